@@ -49,8 +49,12 @@ impl Bio {
             sid_range: start_sid..start_sid + nsectors,
             segments,
             complete_fn,
+            end_io: None,
             status: AtomicU32::new(BioStatus::Init as u32),
             wait_queue: WaitQueue::new(),
+            parent: None,
+            nr_pending_children: AtomicUsize::new(0),
+            children_status: AtomicU32::new(BioStatus::Complete as u32),
         });
         Self(inner)
     }
@@ -210,6 +214,12 @@ impl BioWaiter {
     pub fn wait(&self) -> Option<BioStatus> {
         let mut ret = Some(BioStatus::Complete);
 
+        let _stall = self
+            .bios
+            .iter()
+            .any(|bio| bio.status() == BioStatus::Submit)
+            .then(IoStallGuard::new);
+
         for bio in self.bios.iter() {
             let status = bio.wait_queue.wait_until(|| {
                 let status = bio.status();
@@ -239,6 +249,42 @@ impl Default for BioWaiter {
     }
 }
 
+/// The hooks that are called when a task starts and stops waiting for `Bio` requests.
+static WAIT_HOOKS: Once<(fn(), fn())> = Once::new();
+
+/// Registers the hooks that are called when a task starts (`enter`) and stops (`leave`)
+/// waiting for the completion of `Bio` requests.
+///
+/// The hooks are used to account the time that the tasks are stalled by I/O.
+pub fn register_wait_hooks(enter: fn(), leave: fn()) {
+    WAIT_HOOKS.call_once(|| (enter, leave));
+}
+
+/// Calls the hooks of [`register_wait_hooks`] when it is created and dropped.
+struct IoStallGuard {
+    leave: Option<fn()>,
+}
+
+impl IoStallGuard {
+    fn new() -> Self {
+        let hooks = WAIT_HOOKS.get();
+        if let Some((enter, _)) = hooks {
+            enter();
+        }
+        Self {
+            leave: hooks.map(|(_, leave)| *leave),
+        }
+    }
+}
+
+impl Drop for IoStallGuard {
+    fn drop(&mut self) {
+        if let Some(leave) = self.leave {
+            leave();
+        }
+    }
+}
+
 /// A submitted `Bio` object.
 ///
 /// The request queue of block device only accepts a `SubmittedBio` into the queue.
@@ -271,6 +317,10 @@ impl SubmittedBio {
     /// When the driver finishes the request for this `Bio`, it will call this method.
     pub fn complete(&self, status: BioStatus) {
         assert!(status != BioStatus::Init && status != BioStatus::Submit);
+        let status = match &self.0.end_io {
+            Some(end_io) => end_io(self, status),
+            None => status,
+        };
 
         // Set the status.
         let result = self.0.status.compare_exchange(
@@ -285,9 +335,173 @@ impl SubmittedBio {
         if let Some(complete_fn) = self.0.complete_fn {
             complete_fn(self);
         }
+        if let Some(parent) = &self.0.parent {
+            parent.complete_child(status);
+        }
+    }
+
+    /// Creates a `SubmittedBio` that targets the sectors shifted by `sid_offset`.
+    ///
+    /// The new `SubmittedBio` shares the memory segments with `self`. When it is completed,
+    /// `self` is completed with the same status. This is used by the stacked block devices
+    /// (e.g., partitions) to forward requests to the underlying device.
+    pub(crate) fn remap(self, sid_offset: u64) -> SubmittedBio {
+        let start_sid = self.sid_range().start + sid_offset;
+        let segments = self.segments().to_vec();
+        self.redirect(start_sid, segments, None)
+    }
+
+    /// Splits `self` into `SubmittedBio`s that do not exceed the limits.
+    ///
+    /// Each of the new `SubmittedBio`s holds a contiguous part of the memory segments. `self`
+    /// is completed when all of them are completed, with the status of the first failed one
+    /// (if any). A segment is never divided, so a new `SubmittedBio` with a single segment may
+    /// still exceed `max_nr_sectors`.
+    ///
+    /// If `self` does not exceed the limits, it is returned as is.
+    pub(crate) fn split(self, max_nr_segments: usize, max_nr_sectors: usize) -> Vec<SubmittedBio> {
+        let max_nr_segments = max_nr_segments.max(1);
+
+        let mut chunks: Vec<Range<usize>> = Vec::new();
+        let mut chunk_start = 0;
+        let mut chunk_nsectors = 0;
+        for (index, segment) in self.segments().iter().enumerate() {
+            let nsectors = segment.nsectors().to_raw() as usize;
+            let chunk_len = index - chunk_start;
+            if chunk_len > 0
+                && (chunk_len >= max_nr_segments || chunk_nsectors + nsectors > max_nr_sectors)
+            {
+                chunks.push(chunk_start..index);
+                chunk_start = index;
+                chunk_nsectors = 0;
+            }
+            chunk_nsectors += nsectors;
+        }
+        if chunks.is_empty() {
+            return vec![self];
+        }
+        chunks.push(chunk_start..self.segments().len());
+
+        let mut start_sid = self.sid_range().start;
+        let children = chunks
+            .into_iter()
+            .map(|chunk| {
+                let segments = self.segments()[chunk].to_vec();
+                let child_start_sid = start_sid;
+                start_sid = start_sid
+                    + segments
+                        .iter()
+                        .map(|segment| segment.nsectors().to_raw())
+                        .sum::<u64>();
+                (child_start_sid, segments)
+            })
+            .collect();
+        self.fork(children)
+    }
+
+    /// Forks `self` into `SubmittedBio`s that target the given sectors with the given
+    /// memory segments.
+    ///
+    /// This is used by the stacked block devices (e.g., device mappers) to forward a request
+    /// to one or more underlying devices. `self` is completed when all of the new
+    /// `SubmittedBio`s are completed, with the status of the first failed one (if any).
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `children` is empty.
+    pub fn fork(self, children: Vec<(Sid, Vec<BioSegment>)>) -> Vec<SubmittedBio> {
+        assert!(!children.is_empty());
+        self.0
+            .nr_pending_children
+            .store(children.len(), Ordering::Relaxed);
+        children
+            .into_iter()
+            .map(|(start_sid, segments)| self.new_child(start_sid, segments, None))
+            .collect()
+    }
+
+    /// Redirects `self` to the given sectors with the given memory segments.
+    ///
+    /// This works like [`fork`] with a single child, except that the optional `end_io` is
+    /// called when the new `SubmittedBio` is completed, before `self` is completed. It can
+    /// post-process the data (e.g., decrypt the data that is read) and change the status.
+    ///
+    /// [`fork`]: Self::fork
+    pub fn redirect(
+        self,
+        start_sid: Sid,
+        segments: Vec<BioSegment>,
+        end_io: Option<BioEndIo>,
+    ) -> SubmittedBio {
+        self.0.nr_pending_children.store(1, Ordering::Relaxed);
+        self.new_child(start_sid, segments, end_io)
+    }
+
+    /// Enqueues `self` to the `block_device`, or completes it with an error if the block
+    /// device refuses it.
+    ///
+    /// This is used to submit the `SubmittedBio`s created by [`fork`] and [`redirect`], whose
+    /// submitters have no chance to handle the enqueueing errors.
+    ///
+    /// [`fork`]: Self::fork
+    /// [`redirect`]: Self::redirect
+    pub fn forward(self, block_device: &dyn BlockDevice) {
+        let inner = self.0.clone();
+        if let Err(err) = block_device.enqueue(self) {
+            log::warn!("failed to forward the bio: {:?}", err);
+            SubmittedBio(inner).complete(BioStatus::IoError);
+        }
+    }
+
+    fn new_child(
+        &self,
+        start_sid: Sid,
+        segments: Vec<BioSegment>,
+        end_io: Option<BioEndIo>,
+    ) -> SubmittedBio {
+        let nsectors: u64 = segments
+            .iter()
+            .map(|segment| segment.nsectors().to_raw())
+            .sum();
+        let inner = Arc::new(BioInner {
+            type_: self.type_(),
+            sid_range: start_sid..start_sid + nsectors,
+            segments,
+            complete_fn: None,
+            end_io,
+            status: AtomicU32::new(BioStatus::Submit as u32),
+            wait_queue: WaitQueue::new(),
+            parent: Some(SubmittedBio(self.0.clone())),
+            nr_pending_children: AtomicUsize::new(0),
+            children_status: AtomicU32::new(BioStatus::Complete as u32),
+        });
+        SubmittedBio(inner)
+    }
+
+    /// Records the completion of a child created by `fork` or `redirect`.
+    fn complete_child(&self, status: BioStatus) {
+        if status != BioStatus::Complete {
+            let _ = self.0.children_status.compare_exchange(
+                BioStatus::Complete as u32,
+                status as u32,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            );
+        }
+
+        if self.0.nr_pending_children.fetch_sub(1, Ordering::AcqRel) == 1 {
+            let status =
+                BioStatus::try_from(self.0.children_status.load(Ordering::Relaxed)).unwrap();
+            self.complete(status);
+        }
     }
 }
 
+/// A post-processing function of a `SubmittedBio` created by [`SubmittedBio::redirect`].
+///
+/// It is given the completed `SubmittedBio` and its status, and returns the final status.
+pub type BioEndIo = Box<dyn Fn(&SubmittedBio, BioStatus) -> BioStatus + Send + Sync>;
+
 /// The common inner part of `Bio`.
 struct BioInner {
     /// The type of the I/O
@@ -298,10 +512,18 @@ struct BioInner {
     segments: Vec<BioSegment>,
     /// The I/O completion method
     complete_fn: Option<fn(&SubmittedBio)>,
+    /// The post-processing method that is called before the status is set
+    end_io: Option<BioEndIo>,
     /// The I/O status
     status: AtomicU32,
     /// The wait queue for I/O completion
     wait_queue: WaitQueue,
+    /// The `Bio` that this one is remapped or split from
+    parent: Option<SubmittedBio>,
+    /// The number of the remapped or split children that are not completed
+    nr_pending_children: AtomicUsize,
+    /// The status of the first failed child, or `Complete` if none fails
+    children_status: AtomicU32,
 }
 
 impl BioInner {
@@ -380,6 +602,8 @@ struct BioSegmentInner {
     dma_slice: DmaStreamSlice<DmaStream>,
     /// Whether the segment is allocated from the pool.
     from_pool: bool,
+    /// The segment that this one is sliced from, which is kept alive with this one.
+    origin: Option<Arc<BioSegmentInner>>,
 }
 
 /// The direction of a bio request.
@@ -434,6 +658,7 @@ impl<'a> BioSegment {
                 BioSegmentInner {
                     dma_slice: DmaStreamSlice::new(dma_stream, offset_within_first_block, len),
                     from_pool: false,
+                    origin: None,
                 }
             });
 
@@ -450,6 +675,33 @@ impl<'a> BioSegment {
             inner: Arc::new(BioSegmentInner {
                 dma_slice: DmaStreamSlice::new(dma_stream, 0, len),
                 from_pool: false,
+                origin: None,
+            }),
+        }
+    }
+
+    /// Returns a `BioSegment` that refers to `len` bytes of `self` starting from `offset`.
+    ///
+    /// The memory is shared with `self`.
+    ///
+    /// # Panics
+    ///
+    /// If the `offset` or `len` is not sector aligned, or the range exceeds `self`,
+    /// this method will panic.
+    pub fn slice(&self, offset: usize, len: usize) -> Self {
+        assert!(
+            is_sector_aligned(offset) && is_sector_aligned(len) && offset + len <= self.nbytes()
+        );
+        let dma_slice = &self.inner.dma_slice;
+        Self {
+            inner: Arc::new(BioSegmentInner {
+                dma_slice: DmaStreamSlice::new(
+                    dma_slice.stream().clone(),
+                    dma_slice.offset() + offset,
+                    len,
+                ),
+                from_pool: false,
+                origin: Some(self.inner.clone()),
             }),
         }
     }
@@ -642,6 +894,7 @@ impl BioSegmentPool {
         let bio_segment = BioSegmentInner {
             dma_slice,
             from_pool: true,
+            origin: None,
         };
         Some(bio_segment)
     }
@@ -772,3 +1025,118 @@ impl<const N: u16> AlignedUsize<N> {
         N as usize
     }
 }
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    /// Creates a submitted write `Bio` whose segments have the given numbers of blocks.
+    fn submitted_bio(
+        start_sid: u64,
+        segment_nblocks: &[usize],
+        complete_fn: Option<fn(&SubmittedBio)>,
+    ) -> (Bio, SubmittedBio) {
+        let segments = segment_nblocks
+            .iter()
+            .map(|nblocks| BioSegment::alloc(*nblocks, BioDirection::ToDevice))
+            .collect();
+        let bio = Bio::new(BioType::Write, Sid::new(start_sid), segments, complete_fn);
+        bio.0
+            .status
+            .store(BioStatus::Submit as u32, Ordering::Relaxed);
+        let submitted_bio = SubmittedBio(bio.0.clone());
+        (bio, submitted_bio)
+    }
+
+    fn sid_ranges(bios: &[SubmittedBio]) -> Vec<Range<u64>> {
+        bios.iter()
+            .map(|bio| bio.sid_range().start.to_raw()..bio.sid_range().end.to_raw())
+            .collect()
+    }
+
+    #[ktest]
+    fn split_at_max_nr_segments() {
+        let (_, bio) = submitted_bio(100, &[1; 5], None);
+        let children = bio.split(2, usize::MAX);
+
+        let nr_segments: Vec<_> = children.iter().map(|bio| bio.segments().len()).collect();
+        assert_eq!(nr_segments, vec![2, 2, 1]);
+        assert_eq!(sid_ranges(&children), vec![100..116, 116..132, 132..140]);
+    }
+
+    #[ktest]
+    fn split_at_max_nr_sectors() {
+        let (_, bio) = submitted_bio(0, &[1, 1, 1, 1], None);
+        let children = bio.split(usize::MAX, 20);
+        assert_eq!(sid_ranges(&children), vec![0..16, 16..32]);
+
+        // A segment is never divided even if it exceeds the limit on its own.
+        let (_, bio) = submitted_bio(0, &[1, 3, 1], None);
+        let children = bio.split(usize::MAX, 16);
+        assert_eq!(sid_ranges(&children), vec![0..8, 8..32, 32..40]);
+    }
+
+    #[ktest]
+    fn split_within_limits() {
+        let (bio, submitted_bio) = submitted_bio(0, &[1, 1], None);
+        let children = submitted_bio.split(2, 16);
+
+        assert_eq!(children.len(), 1);
+        assert!(Arc::ptr_eq(&children[0].0, &bio.0));
+    }
+
+    static NR_SPLIT_COMPLETIONS: AtomicUsize = AtomicUsize::new(0);
+
+    #[ktest]
+    fn split_completes_parent_once_with_first_error() {
+        fn complete_fn(_bio: &SubmittedBio) {
+            NR_SPLIT_COMPLETIONS.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let (bio, submitted_bio) = submitted_bio(0, &[1; 3], Some(complete_fn));
+        let children = submitted_bio.split(1, usize::MAX);
+        assert_eq!(children.len(), 3);
+
+        children[1].complete(BioStatus::Complete);
+        children[2].complete(BioStatus::IoError);
+        assert_eq!(bio.status(), BioStatus::Submit);
+        children[0].complete(BioStatus::NoSpace);
+
+        assert_eq!(bio.status(), BioStatus::IoError);
+        assert_eq!(NR_SPLIT_COMPLETIONS.load(Ordering::Relaxed), 1);
+    }
+
+    static NR_REMAP_COMPLETIONS: AtomicUsize = AtomicUsize::new(0);
+
+    #[ktest]
+    fn remap_then_split_completes_parent_once() {
+        fn complete_fn(_bio: &SubmittedBio) {
+            NR_REMAP_COMPLETIONS.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let (bio, submitted_bio) = submitted_bio(8, &[1; 3], Some(complete_fn));
+        let remapped_bio = submitted_bio.remap(64);
+        let children = remapped_bio.split(2, usize::MAX);
+        assert_eq!(sid_ranges(&children), vec![72..88, 88..96]);
+
+        children[0].complete(BioStatus::NotSupported);
+        assert_eq!(bio.status(), BioStatus::Submit);
+        children[1].complete(BioStatus::IoError);
+
+        assert_eq!(bio.status(), BioStatus::NotSupported);
+        assert_eq!(NR_REMAP_COMPLETIONS.load(Ordering::Relaxed), 1);
+    }
+
+    #[ktest]
+    fn remap_then_split_completes_parent_successfully() {
+        let (bio, submitted_bio) = submitted_bio(0, &[1; 2], None);
+        let children = submitted_bio.remap(64).split(1, usize::MAX);
+
+        for child in children.iter() {
+            child.complete(BioStatus::Complete);
+        }
+        assert_eq!(bio.status(), BioStatus::Complete);
+    }
+}
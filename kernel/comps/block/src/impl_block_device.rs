@@ -78,6 +78,14 @@ impl dyn BlockDevice {
         bio.submit(self)
     }
 
+    /// Plugs the device until the returned guard is dropped.
+    ///
+    /// The caller must drop the guard before waiting for the submitted `Bio`s.
+    pub fn plugged(&self) -> BlockPlug<'_> {
+        self.plug();
+        BlockPlug { device: self }
+    }
+
     /// Issues a sync request
     pub fn sync(&self) -> Result<BioStatus, BioEnqueueError> {
         let bio = Bio::new(
@@ -210,6 +218,20 @@ impl dyn BlockDevice {
     }
 }
 
+/// A guard that unplugs the block device when dropped.
+///
+/// See [`BlockDevice::plug`] for details.
+#[must_use]
+pub struct BlockPlug<'a> {
+    device: &'a dyn BlockDevice,
+}
+
+impl Drop for BlockPlug<'_> {
+    fn drop(&mut self) {
+        self.device.unplug();
+    }
+}
+
 fn general_complete_fn(bio: &SubmittedBio) {
     match bio.status() {
         BioStatus::Complete => (),
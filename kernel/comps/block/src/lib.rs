@@ -37,6 +37,7 @@ extern crate alloc;
 pub mod bio;
 pub mod id;
 mod impl_block_device;
+pub mod partition;
 mod prelude;
 pub mod request_queue;
 
@@ -44,6 +45,7 @@ use component::{init_component, ComponentInitError};
 use ostd::sync::SpinLock;
 use spin::Once;
 
+pub use self::impl_block_device::BlockPlug;
 use self::{
     bio::{BioEnqueueError, SubmittedBio},
     prelude::*,
@@ -58,6 +60,20 @@ pub trait BlockDevice: Send + Sync + Any + Debug {
 
     /// Returns the metadata of the block device.
     fn metadata(&self) -> BlockDeviceMeta;
+
+    /// Holds back the processing of the enqueued requests, so that the following
+    /// submissions can be merged with them.
+    ///
+    /// Each call must be paired with a call to [`unplug`]. Prefer `plugged`, which
+    /// returns a guard that unplugs the device when dropped.
+    ///
+    /// [`unplug`]: Self::unplug
+    fn plug(&self) {}
+
+    /// Resumes the processing of the enqueued requests held back by [`plug`].
+    ///
+    /// [`plug`]: Self::plug
+    fn unplug(&self) {}
 }
 
 /// Metadata for a block device.
@@ -85,6 +101,18 @@ pub fn register_device(name: String, device: Arc<dyn BlockDevice>) {
         .insert(name, device);
 }
 
+/// Unregisters the block device named `name`.
+///
+/// The device keeps working for the users that still hold it.
+pub fn unregister_device(name: &str) -> Option<Arc<dyn BlockDevice>> {
+    COMPONENT
+        .get()
+        .unwrap()
+        .block_device_table
+        .lock()
+        .remove(name)
+}
+
 pub fn get_device(str: &str) -> Option<Arc<dyn BlockDevice>> {
     COMPONENT
         .get()
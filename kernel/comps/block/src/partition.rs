@@ -0,0 +1,587 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Partition table scanning.
+//!
+//! Both the GUID Partition Table (GPT) and the legacy Master Boot Record (MBR) are
+//! supported. Each partition found on a disk is exposed as a separate block device,
+//! which forwards the requests to the disk after shifting the sector ids.
+//!
+//! The partitions are named after the disk as Linux does: `vda` has `vda1`, `vda2`, etc.,
+//! while `mmcblk0` has `mmcblk0p1`, `mmcblk0p2`, etc.
+
+use ostd::mm::VmIo;
+
+use crate::{
+    bio::{BioEnqueueError, SubmittedBio},
+    id::Sid,
+    prelude::*,
+    BlockDevice, BlockDeviceMeta, SECTOR_SIZE,
+};
+
+/// A partition of a block device.
+#[derive(Debug)]
+pub struct Partition {
+    disk: Arc<dyn BlockDevice>,
+    info: PartitionInfo,
+}
+
+/// The location and the number of a partition.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PartitionInfo {
+    /// The partition number, starting from 1.
+    pub number: usize,
+    /// The first sector of the partition on the disk.
+    pub start_sector: u64,
+    /// The number of sectors of the partition.
+    pub nr_sectors: u64,
+}
+
+impl Partition {
+    /// Returns the disk that the partition belongs to.
+    pub fn disk(&self) -> &Arc<dyn BlockDevice> {
+        &self.disk
+    }
+
+    /// Returns the location and the number of the partition.
+    pub fn info(&self) -> PartitionInfo {
+        self.info
+    }
+}
+
+impl BlockDevice for Partition {
+    fn enqueue(&self, bio: SubmittedBio) -> Result<(), BioEnqueueError> {
+        if bio.sid_range().end.to_raw() > self.info.nr_sectors {
+            return Err(BioEnqueueError::Refused);
+        }
+        self.disk.enqueue(bio.remap(self.info.start_sector))
+    }
+
+    fn metadata(&self) -> BlockDeviceMeta {
+        BlockDeviceMeta {
+            max_nr_segments_per_bio: self.disk.metadata().max_nr_segments_per_bio,
+            nr_sectors: self.info.nr_sectors as usize,
+        }
+    }
+
+    fn plug(&self) {
+        self.disk.plug();
+    }
+
+    fn unplug(&self) {
+        self.disk.unplug();
+    }
+}
+
+/// Scans the partition table of the block device named `disk_name`, and registers
+/// a block device for each partition found.
+///
+/// The disk must be able to process requests when this function is called, since
+/// the partition table is read synchronously. Returns the names of the registered
+/// partitions.
+pub fn scan_partitions(disk_name: &str) -> Vec<String> {
+    let Some(disk) = crate::get_device(disk_name) else {
+        return Vec::new();
+    };
+    if disk.downcast_ref::<Partition>().is_some() {
+        return Vec::new();
+    }
+
+    let infos = match parse_partition_table(&disk) {
+        Ok(infos) => infos,
+        Err(err) => {
+            log::warn!(
+                "{}: failed to read the partition table: {:?}",
+                disk_name,
+                err
+            );
+            return Vec::new();
+        }
+    };
+
+    let separator = if disk_name.ends_with(|c: char| c.is_ascii_digit()) {
+        "p"
+    } else {
+        ""
+    };
+    let mut names = Vec::with_capacity(infos.len());
+    for info in infos {
+        let name = alloc::format!("{}{}{}", disk_name, separator, info.number);
+        log::info!(
+            "{}: sectors {}..{}",
+            name,
+            info.start_sector,
+            info.start_sector + info.nr_sectors
+        );
+        let partition = Partition {
+            disk: disk.clone(),
+            info,
+        };
+        crate::register_device(name.clone(), Arc::new(partition));
+        names.push(name);
+    }
+    names
+}
+
+/// Parses the partition table of the disk.
+///
+/// A GPT is used if the MBR is a protective one. Partitions that exceed the disk are
+/// ignored. Returns an empty list if the disk is not partitioned.
+pub fn parse_partition_table(disk: &Arc<dyn BlockDevice>) -> ostd::Result<Vec<PartitionInfo>> {
+    let disk_sectors = disk.metadata().nr_sectors as u64;
+    if disk_sectors < 2 {
+        return Ok(Vec::new());
+    }
+
+    let mut mbr = [0u8; SECTOR_SIZE];
+    disk.read_bytes(0, &mut mbr)?;
+    let Some(entries) = mbr::parse_entries(&mbr) else {
+        return Ok(Vec::new());
+    };
+
+    let mut infos = if entries.iter().any(|e| e.type_ == mbr::TYPE_GPT_PROTECTIVE) {
+        gpt::parse(disk, disk_sectors)?
+    } else {
+        mbr::parse(disk, &entries)?
+    };
+    infos.retain(|info| {
+        info.nr_sectors > 0
+            && info
+                .start_sector
+                .checked_add(info.nr_sectors)
+                .is_some_and(|end| end <= disk_sectors)
+    });
+    Ok(infos)
+}
+
+fn read_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(buf[offset..offset + 2].try_into().unwrap())
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(buf: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(buf[offset..offset + 8].try_into().unwrap())
+}
+
+mod mbr {
+    use super::*;
+
+    /// The type of the protective MBR entry that covers a GPT disk.
+    pub(super) const TYPE_GPT_PROTECTIVE: u8 = 0xEE;
+    /// The types of the extended partitions.
+    const TYPES_EXTENDED: [u8; 3] = [0x05, 0x0F, 0x85];
+
+    const BOOT_SIGNATURE: u16 = 0xAA55;
+    /// The OEM names of the filesystems whose boot sectors look like an MBR.
+    const NON_MBR_OEM_NAMES: [&[u8]; 2] = [b"EXFAT   ", b"NTFS    "];
+    const ENTRIES_OFFSET: usize = 446;
+    const ENTRY_SIZE: usize = 16;
+    /// The logical partitions in the extended partition are numbered from 5.
+    const FIRST_LOGICAL_NUMBER: usize = 5;
+    /// The maximum number of logical partitions, to stop at looping EBR chains.
+    const MAX_LOGICAL_PARTITIONS: usize = 128;
+
+    /// A partition entry in an MBR or an EBR.
+    #[derive(Clone, Copy, Debug)]
+    pub(super) struct Entry {
+        pub(super) type_: u8,
+        pub(super) start_lba: u32,
+        pub(super) nr_sectors: u32,
+    }
+
+    /// Parses the four entries of a boot record, or returns `None` if it is not a valid one.
+    ///
+    /// The boot sectors of some filesystems (e.g., exFAT) also carry the boot signature,
+    /// so the boot indicators of the entries are checked as well.
+    pub(super) fn parse_entries(sector: &[u8]) -> Option<[Entry; 4]> {
+        if read_u16(sector, 510) != BOOT_SIGNATURE {
+            return None;
+        }
+        if NON_MBR_OEM_NAMES.contains(&&sector[3..11]) {
+            return None;
+        }
+
+        let raw_entry = |i: usize| &sector[ENTRIES_OFFSET + i * ENTRY_SIZE..][..ENTRY_SIZE];
+        if (0..4).any(|i| !matches!(raw_entry(i)[0], 0x00 | 0x80)) {
+            return None;
+        }
+        Some(core::array::from_fn(|i| {
+            let entry = raw_entry(i);
+            Entry {
+                type_: entry[4],
+                start_lba: read_u32(entry, 8),
+                nr_sectors: read_u32(entry, 12),
+            }
+        }))
+    }
+
+    pub(super) fn parse(
+        disk: &Arc<dyn BlockDevice>,
+        entries: &[Entry; 4],
+    ) -> ostd::Result<Vec<PartitionInfo>> {
+        let mut infos = Vec::new();
+        let mut extended = None;
+
+        for (i, entry) in entries.iter().enumerate() {
+            if entry.type_ == 0 || entry.nr_sectors == 0 {
+                continue;
+            }
+            if TYPES_EXTENDED.contains(&entry.type_) {
+                extended.get_or_insert(*entry);
+                continue;
+            }
+            infos.push(PartitionInfo {
+                number: i + 1,
+                start_sector: entry.start_lba as u64,
+                nr_sectors: entry.nr_sectors as u64,
+            });
+        }
+
+        if let Some(extended) = extended {
+            parse_logical(disk, &extended, &mut infos)?;
+        }
+        Ok(infos)
+    }
+
+    /// Follows the chain of the Extended Boot Records (EBRs).
+    ///
+    /// The first entry of an EBR describes a logical partition relative to the EBR itself,
+    /// and the second entry points to the next EBR relative to the extended partition.
+    fn parse_logical(
+        disk: &Arc<dyn BlockDevice>,
+        extended: &Entry,
+        infos: &mut Vec<PartitionInfo>,
+    ) -> ostd::Result<()> {
+        let extended_start = extended.start_lba as u64;
+        let mut ebr_lba = extended_start;
+        let mut sector = [0u8; SECTOR_SIZE];
+
+        for i in 0..MAX_LOGICAL_PARTITIONS {
+            disk.read_bytes(Sid::new(ebr_lba).to_offset(), &mut sector)?;
+            let Some(entries) = parse_entries(&sector) else {
+                break;
+            };
+
+            let logical = &entries[0];
+            if logical.type_ != 0 && logical.nr_sectors != 0 {
+                infos.push(PartitionInfo {
+                    number: FIRST_LOGICAL_NUMBER + i,
+                    start_sector: ebr_lba + logical.start_lba as u64,
+                    nr_sectors: logical.nr_sectors as u64,
+                });
+            }
+
+            let next = &entries[1];
+            if !TYPES_EXTENDED.contains(&next.type_) || next.start_lba == 0 {
+                break;
+            }
+            ebr_lba = extended_start + next.start_lba as u64;
+        }
+        Ok(())
+    }
+}
+
+mod gpt {
+    use super::*;
+
+    const SIGNATURE: &[u8; 8] = b"EFI PART";
+    const MIN_HEADER_SIZE: usize = 92;
+    const MIN_ENTRY_SIZE: usize = 128;
+    /// The maximum number of entries, to bound the size of the entry array.
+    const MAX_ENTRIES: usize = 1024;
+
+    /// The fields of a GPT header used for locating the partition entries.
+    struct Header {
+        entries_lba: u64,
+        nr_entries: usize,
+        entry_size: usize,
+        entries_crc32: u32,
+    }
+
+    pub(super) fn parse(
+        disk: &Arc<dyn BlockDevice>,
+        disk_sectors: u64,
+    ) -> ostd::Result<Vec<PartitionInfo>> {
+        // Fall back to the backup header at the last sector if the primary one is corrupted.
+        for header_lba in [1, disk_sectors - 1] {
+            let Some(header) = read_header(disk, header_lba, disk_sectors)? else {
+                continue;
+            };
+            if let Some(infos) = read_entries(disk, &header)? {
+                return Ok(infos);
+            }
+        }
+
+        log::warn!("no valid GPT is found on a disk with a protective MBR");
+        Ok(Vec::new())
+    }
+
+    fn read_header(
+        disk: &Arc<dyn BlockDevice>,
+        lba: u64,
+        disk_sectors: u64,
+    ) -> ostd::Result<Option<Header>> {
+        let mut sector = [0u8; SECTOR_SIZE];
+        disk.read_bytes(Sid::new(lba).to_offset(), &mut sector)?;
+
+        if &sector[0..8] != SIGNATURE {
+            return Ok(None);
+        }
+        let header_size = read_u32(&sector, 12) as usize;
+        if !(MIN_HEADER_SIZE..=SECTOR_SIZE).contains(&header_size) {
+            return Ok(None);
+        }
+        let header_crc32 = read_u32(&sector, 16);
+        sector[16..20].fill(0);
+        if crc32(&sector[..header_size]) != header_crc32 || read_u64(&sector, 24) != lba {
+            return Ok(None);
+        }
+
+        let nr_entries = read_u32(&sector, 80) as usize;
+        let entry_size = read_u32(&sector, 84) as usize;
+        if nr_entries > MAX_ENTRIES || entry_size < MIN_ENTRY_SIZE || !entry_size.is_power_of_two()
+        {
+            return Ok(None);
+        }
+
+        // The header comes from the disk, so the entry array is checked to be on the disk.
+        let entries_lba = read_u64(&sector, 72);
+        let entries_fit = nr_entries
+            .checked_mul(entry_size)
+            .map(|nbytes| nbytes.div_ceil(SECTOR_SIZE) as u64)
+            .and_then(|nr_sectors| entries_lba.checked_add(nr_sectors))
+            .is_some_and(|end| end <= disk_sectors);
+        if !entries_fit || entries_lba.checked_mul(SECTOR_SIZE as u64).is_none() {
+            return Ok(None);
+        }
+
+        Ok(Some(Header {
+            entries_lba,
+            nr_entries,
+            entry_size,
+            entries_crc32: read_u32(&sector, 88),
+        }))
+    }
+
+    fn read_entries(
+        disk: &Arc<dyn BlockDevice>,
+        header: &Header,
+    ) -> ostd::Result<Option<Vec<PartitionInfo>>> {
+        let nbytes = header.nr_entries * header.entry_size;
+        let mut entries = vec![0u8; nbytes.next_multiple_of(SECTOR_SIZE)];
+        disk.read_bytes(Sid::new(header.entries_lba).to_offset(), &mut entries)?;
+        if crc32(&entries[..nbytes]) != header.entries_crc32 {
+            return Ok(None);
+        }
+
+        let infos = entries[..nbytes]
+            .chunks_exact(header.entry_size)
+            .enumerate()
+            .filter(|(_, entry)| entry[0..16].iter().any(|b| *b != 0))
+            .filter_map(|(i, entry)| {
+                let first_lba = read_u64(entry, 32);
+                let last_lba = read_u64(entry, 40);
+                (last_lba >= first_lba).then_some(PartitionInfo {
+                    number: i + 1,
+                    start_sector: first_lba,
+                    nr_sectors: last_lba - first_lba + 1,
+                })
+            })
+            .collect();
+        Ok(Some(infos))
+    }
+
+    /// Computes the CRC-32 (IEEE 802.3) checksum used by GPT.
+    pub(super) fn crc32(data: &[u8]) -> u32 {
+        let mut crc = !0u32;
+        for byte in data {
+            crc ^= *byte as u32;
+            for _ in 0..8 {
+                let mask = (crc & 1).wrapping_neg();
+                crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+            }
+        }
+        !crc
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::{
+        mm::{FrameAllocOptions, Segment, VmIo},
+        prelude::*,
+    };
+
+    use super::*;
+    use crate::bio::{BioStatus, BioType};
+
+    /// A disk backed by memory, which completes the requests synchronously.
+    #[derive(Debug)]
+    struct MemoryDisk {
+        blocks: Segment<()>,
+    }
+
+    impl MemoryDisk {
+        fn new(nblocks: usize) -> Self {
+            let blocks = FrameAllocOptions::new().alloc_segment(nblocks).unwrap();
+            Self { blocks }
+        }
+
+        fn write_sector(&self, lba: u64, offset: usize, data: &[u8]) {
+            self.blocks
+                .write_bytes(lba as usize * SECTOR_SIZE + offset, data)
+                .unwrap();
+        }
+    }
+
+    impl BlockDevice for MemoryDisk {
+        fn enqueue(&self, bio: SubmittedBio) -> core::result::Result<(), BioEnqueueError> {
+            let mut offset = bio.sid_range().start.to_offset();
+            for segment in bio.segments() {
+                let size = match bio.type_() {
+                    BioType::Read => segment
+                        .inner_segment()
+                        .writer()
+                        .write(self.blocks.reader().skip(offset)),
+                    BioType::Write => self
+                        .blocks
+                        .writer()
+                        .skip(offset)
+                        .write(&mut segment.inner_segment().reader()),
+                    _ => 0,
+                };
+                offset += size;
+            }
+            bio.complete(BioStatus::Complete);
+            Ok(())
+        }
+
+        fn metadata(&self) -> BlockDeviceMeta {
+            BlockDeviceMeta {
+                max_nr_segments_per_bio: usize::MAX,
+                nr_sectors: self.blocks.size() / SECTOR_SIZE,
+            }
+        }
+    }
+
+    fn mbr_entry(type_: u8, start_lba: u32, nr_sectors: u32) -> [u8; 16] {
+        let mut entry = [0u8; 16];
+        entry[4] = type_;
+        entry[8..12].copy_from_slice(&start_lba.to_le_bytes());
+        entry[12..16].copy_from_slice(&nr_sectors.to_le_bytes());
+        entry
+    }
+
+    fn write_boot_record(disk: &MemoryDisk, lba: u64, entries: &[[u8; 16]]) {
+        for (i, entry) in entries.iter().enumerate() {
+            disk.write_sector(lba, 446 + i * 16, entry);
+        }
+        disk.write_sector(lba, 510, &[0x55, 0xAA]);
+    }
+
+    #[ktest]
+    fn mbr_with_logical_partitions() {
+        // 16 pages = 128 sectors.
+        let disk = Arc::new(MemoryDisk::new(16));
+        write_boot_record(&disk, 0, &[mbr_entry(0x83, 2, 30), mbr_entry(0x05, 40, 80)]);
+        // The first EBR at sector 40 and the second one at sector 80.
+        write_boot_record(
+            &disk,
+            40,
+            &[mbr_entry(0x83, 1, 20), mbr_entry(0x05, 40, 40)],
+        );
+        write_boot_record(&disk, 80, &[mbr_entry(0x83, 2, 30)]);
+
+        let infos = parse_partition_table(&(disk as Arc<dyn BlockDevice>)).unwrap();
+        assert_eq!(
+            infos,
+            vec![
+                PartitionInfo {
+                    number: 1,
+                    start_sector: 2,
+                    nr_sectors: 30
+                },
+                PartitionInfo {
+                    number: 5,
+                    start_sector: 41,
+                    nr_sectors: 20
+                },
+                PartitionInfo {
+                    number: 6,
+                    start_sector: 82,
+                    nr_sectors: 30
+                },
+            ]
+        );
+    }
+
+    fn write_gpt_header(disk: &MemoryDisk, lba: u64, entries_lba: u64, entries: &[u8]) {
+        let mut header = [0u8; 92];
+        header[0..8].copy_from_slice(b"EFI PART");
+        header[12..16].copy_from_slice(&92u32.to_le_bytes());
+        header[24..32].copy_from_slice(&lba.to_le_bytes());
+        header[72..80].copy_from_slice(&entries_lba.to_le_bytes());
+        header[80..84].copy_from_slice(&((entries.len() / 128) as u32).to_le_bytes());
+        header[84..88].copy_from_slice(&128u32.to_le_bytes());
+        header[88..92].copy_from_slice(&gpt::crc32(entries).to_le_bytes());
+        let header_crc32 = gpt::crc32(&header);
+        header[16..20].copy_from_slice(&header_crc32.to_le_bytes());
+        disk.write_sector(lba, 0, &header);
+    }
+
+    #[ktest]
+    fn gpt_with_protective_mbr() {
+        let disk = Arc::new(MemoryDisk::new(16));
+        write_boot_record(&disk, 0, &[mbr_entry(0xEE, 1, 127)]);
+
+        let mut entries = [0u8; 4 * 128];
+        for (i, (first, last)) in [(34u64, 63u64), (64, 95)].iter().enumerate() {
+            let entry = &mut entries[i * 128..(i + 1) * 128];
+            entry[0..16].fill(0xAB);
+            entry[32..40].copy_from_slice(&first.to_le_bytes());
+            entry[40..48].copy_from_slice(&last.to_le_bytes());
+        }
+        disk.write_sector(2, 0, &entries);
+
+        write_gpt_header(&disk, 1, 2, &entries);
+
+        let infos = parse_partition_table(&(disk as Arc<dyn BlockDevice>)).unwrap();
+        assert_eq!(
+            infos,
+            vec![
+                PartitionInfo {
+                    number: 1,
+                    start_sector: 34,
+                    nr_sectors: 30
+                },
+                PartitionInfo {
+                    number: 2,
+                    start_sector: 64,
+                    nr_sectors: 32
+                },
+            ]
+        );
+    }
+
+    #[ktest]
+    fn gpt_with_entries_out_of_disk() {
+        let disk = Arc::new(MemoryDisk::new(16));
+        write_boot_record(&disk, 0, &[mbr_entry(0xEE, 1, 127)]);
+
+        // The headers have valid CRCs, but their entry arrays are beyond the disk or make the
+        // byte offset overflow.
+        let entries = [0u8; 4 * 128];
+        write_gpt_header(&disk, 1, u64::MAX / 2, &entries);
+        write_gpt_header(&disk, 127, 128, &entries);
+
+        let infos = parse_partition_table(&(disk as Arc<dyn BlockDevice>)).unwrap();
+        assert!(infos.is_empty());
+    }
+
+    #[ktest]
+    fn crc32_check_value() {
+        assert_eq!(gpt::crc32(b"123456789"), 0xCBF4_3926);
+    }
+}
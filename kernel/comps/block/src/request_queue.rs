@@ -1,6 +1,11 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use ostd::sync::{Mutex, WaitQueue};
+use core::time::Duration;
+
+use ostd::{
+    sync::{Mutex, WaitQueue},
+    timer::Jiffies,
+};
 
 use super::{
     bio::{BioEnqueueError, BioType, SubmittedBio},
@@ -8,40 +13,96 @@ use super::{
 };
 use crate::prelude::*;
 
-/// A simple block I/O request queue backed by one internal FIFO queue.
+/// A block I/O request queue with a deadline I/O scheduler.
 ///
-/// It is a FIFO producer-consumer queue, where the producer (e.g., filesystem)
+/// It is a producer-consumer queue, where the producer (e.g., filesystem)
 /// submits requests to the queue, and the consumer (e.g., block device driver)
 /// continuously consumes and processes these requests from the queue.
 ///
-/// It supports merging the new request with the front request if if the type
-/// is same and the sector range is contiguous.
+/// The queue works in the following way, which is similar to the `mq-deadline`
+/// scheduler of Linux:
+/// - A new `SubmittedBio` is merged into a pending request of the same type if
+///   their sector ranges are contiguous, as long as the merged request does not
+///   exceed the [`QueueLimits`]. A `SubmittedBio` that exceeds the limits is split
+///   before being enqueued.
+/// - The pending requests are dispatched in the ascending order of their sectors,
+///   starting from the end of the last dispatched request and wrapping around at the
+///   end of the device. However, the oldest request is dispatched first if it has
+///   waited for longer than its deadline, so that no request is starved.
+/// - A flush request is dispatched after all requests enqueued before it, and
+///   the `SubmittedBio`s enqueued after it are not merged into those requests.
+/// - The dispatching can be held back by [`plug`]ging the queue, so that a batch
+///   of submissions has the chance to be merged before the driver sees them.
+///
+/// [`plug`]: Self::plug
 pub struct BioRequestSingleQueue {
-    queue: Mutex<VecDeque<BioRequest>>,
+    scheduler: Mutex<Scheduler>,
     num_requests: AtomicUsize,
+    num_plugs: AtomicUsize,
     wait_queue: WaitQueue,
-    max_nr_segments_per_bio: usize,
+    limits: QueueLimits,
+}
+
+/// The limits of the requests in a [`BioRequestSingleQueue`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QueueLimits {
+    /// The maximum number of segments in a request.
+    pub max_nr_segments: usize,
+    /// The maximum number of sectors in a request.
+    pub max_nr_sectors: usize,
+}
+
+impl Default for QueueLimits {
+    fn default() -> Self {
+        Self {
+            max_nr_segments: usize::MAX,
+            max_nr_sectors: usize::MAX,
+        }
+    }
 }
 
+/// The time after which a pending read request is dispatched in priority.
+const READ_EXPIRE: Duration = Duration::from_millis(500);
+/// The time after which a pending write or discard request is dispatched in priority.
+const WRITE_EXPIRE: Duration = Duration::from_secs(5);
+/// The number of pending requests that dispatches the requests regardless of plugs.
+const MAX_PLUGGED_REQUESTS: usize = 32;
+/// The number of the preceding requests examined when looking for a back merge.
+const BACK_MERGE_LOOKUP: usize = 8;
+
 impl BioRequestSingleQueue {
     /// Creates an empty queue.
     pub fn new() -> Self {
-        Self::with_max_nr_segments_per_bio(usize::MAX)
+        Self::with_limits(QueueLimits::default())
     }
 
     /// Creates an empty queue with the upper bound for the number of segments in a bio.
     pub fn with_max_nr_segments_per_bio(max_nr_segments_per_bio: usize) -> Self {
+        Self::with_limits(QueueLimits {
+            max_nr_segments: max_nr_segments_per_bio,
+            ..QueueLimits::default()
+        })
+    }
+
+    /// Creates an empty queue with the limits of the requests.
+    pub fn with_limits(limits: QueueLimits) -> Self {
         Self {
-            queue: Mutex::new(VecDeque::new()),
+            scheduler: Mutex::new(Scheduler::new()),
             num_requests: AtomicUsize::new(0),
+            num_plugs: AtomicUsize::new(0),
             wait_queue: WaitQueue::new(),
-            max_nr_segments_per_bio,
+            limits,
         }
     }
 
     /// Returns the upper limit for the number of segments per bio.
     pub fn max_nr_segments_per_bio(&self) -> usize {
-        self.max_nr_segments_per_bio
+        self.limits.max_nr_segments
+    }
+
+    /// Returns the limits of the requests.
+    pub fn limits(&self) -> QueueLimits {
+        self.limits
     }
 
     /// Returns the number of requests currently in this queue.
@@ -51,67 +112,72 @@ impl BioRequestSingleQueue {
 
     /// Enqueues a `SubmittedBio` to this queue.
     ///
-    /// When enqueueing the `SubmittedBio`, try to insert it into the last request if the
-    /// type is same and the sector range is contiguous.
-    /// Otherwise, creates and inserts a new request for the `SubmittedBio`.
+    /// The `SubmittedBio` is split if it exceeds the limits of the queue. Then each part is
+    /// merged into a pending request if possible, or is inserted as a new request.
     ///
     /// This method will wake up the waiter if a new `BioRequest` is enqueued.
     pub fn enqueue(&self, bio: SubmittedBio) -> Result<(), BioEnqueueError> {
-        if bio.segments().len() >= self.max_nr_segments_per_bio {
-            return Err(BioEnqueueError::TooBig);
-        }
+        let bios = if bio.type_() == BioType::Flush {
+            vec![bio]
+        } else {
+            bio.split(self.limits.max_nr_segments, self.limits.max_nr_sectors)
+        };
 
-        let mut queue = self.queue.lock();
-        if let Some(request) = queue.front_mut() {
-            if request.can_merge(&bio)
-                && request.num_segments() + bio.segments().len() <= self.max_nr_segments_per_bio
-            {
-                request.merge_bio(bio);
-                return Ok(());
+        let mut num_new_requests = 0;
+        let mut scheduler = self.scheduler.lock();
+        for bio in bios {
+            if scheduler.insert(bio, &self.limits) {
+                num_new_requests += 1;
             }
         }
+        self.num_requests
+            .fetch_add(num_new_requests, Ordering::Relaxed);
+        drop(scheduler);
 
-        let new_request = BioRequest::from(bio);
-        queue.push_front(new_request);
-        self.inc_num_requests();
-        drop(queue);
-
-        self.wait_queue.wake_all();
+        if num_new_requests > 0 {
+            self.wait_queue.wake_all();
+        }
         Ok(())
     }
 
     /// Dequeues a `BioRequest` from this queue.
     ///
-    /// This method will wait until one request can be retrieved.
+    /// This method will wait until one request can be retrieved and the queue is not
+    /// plugged.
     pub fn dequeue(&self) -> BioRequest {
-        let mut num_requests = self.num_requests();
-
         loop {
-            if num_requests > 0 {
-                let mut queue = self.queue.lock();
-                if let Some(request) = queue.pop_back() {
-                    self.dec_num_requests();
-                    return request;
-                }
-            }
+            self.wait_queue
+                .wait_until(|| self.can_dispatch().then_some(()));
 
-            num_requests = self.wait_queue.wait_until(|| {
-                let num_requests = self.num_requests();
-                if num_requests > 0 {
-                    Some(num_requests)
-                } else {
-                    None
-                }
-            });
+            let mut scheduler = self.scheduler.lock();
+            if let Some(request) = scheduler.dispatch() {
+                self.num_requests.fetch_sub(1, Ordering::Relaxed);
+                return request;
+            }
         }
     }
 
-    fn dec_num_requests(&self) {
-        self.num_requests.fetch_sub(1, Ordering::Relaxed);
+    /// Plugs the queue.
+    ///
+    /// The requests are not dispatched until the queue is unplugged as many times as
+    /// it has been plugged, unless too many requests are pending.
+    pub fn plug(&self) {
+        self.num_plugs.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Unplugs the queue.
+    pub fn unplug(&self) {
+        let old_num_plugs = self.num_plugs.fetch_sub(1, Ordering::Relaxed);
+        debug_assert!(old_num_plugs > 0);
+        if old_num_plugs == 1 {
+            self.wait_queue.wake_all();
+        }
     }
 
-    fn inc_num_requests(&self) {
-        self.num_requests.fetch_add(1, Ordering::Relaxed);
+    fn can_dispatch(&self) -> bool {
+        let num_requests = self.num_requests();
+        num_requests > 0
+            && (self.num_plugs.load(Ordering::Relaxed) == 0 || num_requests >= MAX_PLUGGED_REQUESTS)
     }
 }
 
@@ -125,11 +191,206 @@ impl Debug for BioRequestSingleQueue {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         f.debug_struct("BioRequestSingleQueue")
             .field("num_requests", &self.num_requests())
-            .field("queue", &self.queue.lock())
+            .field("limits", &self.limits)
+            .field("scheduler", &self.scheduler.lock())
             .finish()
     }
 }
 
+/// The key of a pending request in the sorted map, i.e., its start sector and its
+/// sequence number.
+type RequestKey = (u64, u64);
+
+/// The state of the deadline I/O scheduler.
+#[derive(Debug)]
+struct Scheduler {
+    /// The pending requests except flushes, sorted by their start sectors.
+    sorted: BTreeMap<RequestKey, PendingRequest>,
+    /// The start sectors of the pending requests except flushes, indexed by their
+    /// sequence numbers (i.e., in the arrival order).
+    fifo: BTreeMap<u64, u64>,
+    /// The pending flush requests with their sequence numbers, in the arrival order.
+    flushes: VecDeque<(u64, BioRequest)>,
+    /// The sequence number of the next new request.
+    next_seq: u64,
+    /// The sector following the last dispatched request.
+    head: u64,
+}
+
+#[derive(Debug)]
+struct PendingRequest {
+    request: BioRequest,
+    deadline: Duration,
+}
+
+impl Scheduler {
+    fn new() -> Self {
+        Self {
+            sorted: BTreeMap::new(),
+            fifo: BTreeMap::new(),
+            flushes: VecDeque::new(),
+            next_seq: 0,
+            head: 0,
+        }
+    }
+
+    /// Inserts a `SubmittedBio`.
+    ///
+    /// Returns `true` if a new request is created, `false` if it is merged.
+    fn insert(&mut self, bio: SubmittedBio, limits: &QueueLimits) -> bool {
+        let bio = if bio.type_() == BioType::Flush {
+            bio
+        } else {
+            match self.try_merge(bio, limits) {
+                Ok(()) => return false,
+                Err(bio) => bio,
+            }
+        };
+
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        let request = BioRequest::from(bio);
+
+        if request.type_() == BioType::Flush {
+            self.flushes.push_back((seq, request));
+            return true;
+        }
+
+        let expire = if request.type_() == BioType::Read {
+            READ_EXPIRE
+        } else {
+            WRITE_EXPIRE
+        };
+        let start = request.sid_range().start.to_raw();
+        self.fifo.insert(seq, start);
+        self.sorted.insert(
+            (start, seq),
+            PendingRequest {
+                request,
+                deadline: Jiffies::elapsed().as_duration() + expire,
+            },
+        );
+        true
+    }
+
+    /// Tries to merge `bio` into a pending request.
+    ///
+    /// Returns `bio` back if it cannot be merged.
+    fn try_merge(&mut self, bio: SubmittedBio, limits: &QueueLimits) -> Result<(), SubmittedBio> {
+        let start = bio.sid_range().start.to_raw();
+        let end = bio.sid_range().end.to_raw();
+        let after_flushes = self.after_flushes();
+        let fits = |request: &BioRequest| {
+            request.type_() == bio.type_()
+                && request.num_segments() + bio.segments().len() <= limits.max_nr_segments
+                && request.num_sectors() + (end - start) as usize <= limits.max_nr_sectors
+        };
+
+        // Back merge: append `bio` to a request that ends at `start`.
+        let back_key = self
+            .sorted
+            .range(..(start, 0))
+            .rev()
+            .take(BACK_MERGE_LOOKUP)
+            .find(|((_, seq), pending)| {
+                pending.request.sid_range().end.to_raw() == start
+                    && after_flushes(*seq)
+                    && fits(&pending.request)
+            })
+            .map(|(key, _)| *key);
+        // Front merge: prepend `bio` to a request that starts at `end`.
+        let front_key = self
+            .sorted
+            .range((end, 0)..(end + 1, 0))
+            .find(|((_, seq), pending)| after_flushes(*seq) && fits(&pending.request))
+            .map(|(key, _)| *key);
+
+        if let Some(key) = back_key {
+            let pending = self.sorted.get_mut(&key).unwrap();
+            pending.request.merge_bio(bio);
+            self.coalesce_next(key, limits);
+            Ok(())
+        } else if let Some(key) = front_key {
+            let mut pending = self.sorted.remove(&key).unwrap();
+            pending.request.merge_bio(bio);
+            let (_, seq) = key;
+            self.fifo.insert(seq, start);
+            self.sorted.insert((start, seq), pending);
+            Ok(())
+        } else {
+            Err(bio)
+        }
+    }
+
+    /// Merges the request following the request of `key` into it, if they become contiguous.
+    fn coalesce_next(&mut self, key: RequestKey, limits: &QueueLimits) {
+        let after_flushes = self.after_flushes();
+        let pending = &self.sorted[&key];
+        let end = pending.request.sid_range().end.to_raw();
+        let next_key = self
+            .sorted
+            .range((end, 0)..(end + 1, 0))
+            .find(|((_, seq), next)| {
+                after_flushes(*seq) && pending.request.can_merge_request(&next.request, limits)
+            })
+            .map(|(key, _)| *key);
+
+        if let Some(next_key) = next_key {
+            let next = self.sorted.remove(&next_key).unwrap();
+            self.fifo.remove(&next_key.1);
+            let pending = self.sorted.get_mut(&key).unwrap();
+            pending.deadline = pending.deadline.min(next.deadline);
+            pending.request.merge_request(next.request);
+        }
+    }
+
+    /// Returns a predicate that checks whether the request of a sequence number is enqueued
+    /// after all the pending flushes.
+    ///
+    /// Only such requests can be merged with. A request enqueued before a flush is dispatched
+    /// ahead of the flush, so a bio submitted after the flush must not be merged into it.
+    fn after_flushes(&self) -> impl Fn(u64) -> bool {
+        let last_flush_seq = self.flushes.back().map(|(seq, _)| *seq);
+        move |seq| last_flush_seq.is_none_or(|flush_seq| seq > flush_seq)
+    }
+
+    /// Picks the next request to dispatch.
+    fn dispatch(&mut self) -> Option<BioRequest> {
+        let oldest = self
+            .fifo
+            .first_key_value()
+            .map(|(seq, start)| (*start, *seq));
+
+        if let Some(flush_seq) = self.flushes.front().map(|(seq, _)| *seq) {
+            match oldest {
+                // Drain the requests enqueued before the flush in the arrival order.
+                Some(key) if key.1 < flush_seq => return self.take(key),
+                _ => return self.flushes.pop_front().map(|(_, request)| request),
+            }
+        }
+
+        let oldest = oldest?;
+        if self.sorted[&oldest].deadline <= Jiffies::elapsed().as_duration() {
+            return self.take(oldest);
+        }
+
+        let next_key = self
+            .sorted
+            .range((self.head, 0)..)
+            .next()
+            .or_else(|| self.sorted.first_key_value())
+            .map(|(key, _)| *key)?;
+        self.take(next_key)
+    }
+
+    fn take(&mut self, key: RequestKey) -> Option<BioRequest> {
+        let pending = self.sorted.remove(&key)?;
+        self.fifo.remove(&key.1);
+        self.head = pending.request.sid_range().end.to_raw();
+        Some(pending.request)
+    }
+}
+
 /// The block I/O request.
 ///
 /// The advantage of this data structure is to merge several `SubmittedBio`s that are
@@ -209,6 +470,24 @@ impl BioRequest {
     }
 }
 
+impl BioRequest {
+    /// Returns `true` if `other` can be appended to this request without exceeding `limits`.
+    fn can_merge_request(&self, other: &BioRequest, limits: &QueueLimits) -> bool {
+        self.type_ == other.type_
+            && self.sid_range.end == other.sid_range.start
+            && self.num_segments + other.num_segments <= limits.max_nr_segments
+            && self.num_sectors() + other.num_sectors() <= limits.max_nr_sectors
+    }
+
+    /// Appends the `SubmittedBio`s of `other` to this request.
+    fn merge_request(&mut self, other: BioRequest) {
+        debug_assert_eq!(self.sid_range.end, other.sid_range.start);
+        self.sid_range.end = other.sid_range.end;
+        self.num_segments += other.num_segments;
+        self.bios.extend(other.bios);
+    }
+}
+
 impl From<SubmittedBio> for BioRequest {
     fn from(bio: SubmittedBio) -> Self {
         Self {
@@ -223,3 +502,190 @@ impl From<SubmittedBio> for BioRequest {
         }
     }
 }
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+    use crate::{
+        bio::{Bio, BioDirection, BioSegment},
+        BlockDevice, BlockDeviceMeta,
+    };
+
+    /// A block device that only queues the requests.
+    #[derive(Debug)]
+    struct QueueDevice {
+        queue: BioRequestSingleQueue,
+    }
+
+    impl QueueDevice {
+        fn new(limits: QueueLimits) -> Self {
+            Self {
+                queue: BioRequestSingleQueue::with_limits(limits),
+            }
+        }
+
+        /// Submits a `Bio` with `nblocks` single-block segments.
+        fn submit(&self, type_: BioType, start_sid: u64, nblocks: usize) {
+            let direction = if type_ == BioType::Read {
+                BioDirection::FromDevice
+            } else {
+                BioDirection::ToDevice
+            };
+            let segments = (0..nblocks)
+                .map(|_| BioSegment::alloc(1, direction))
+                .collect();
+            let bio = Bio::new(type_, Sid::new(start_sid), segments, None);
+            bio.submit(self).unwrap();
+        }
+
+        /// Dequeues a request and returns its type, its sectors, and its number of bios.
+        fn dequeue(&self) -> (BioType, Range<u64>, usize) {
+            let request = self.queue.dequeue();
+            let sid_range = request.sid_range();
+            (
+                request.type_(),
+                sid_range.start.to_raw()..sid_range.end.to_raw(),
+                request.bios().count(),
+            )
+        }
+    }
+
+    impl BlockDevice for QueueDevice {
+        fn enqueue(&self, bio: SubmittedBio) -> core::result::Result<(), BioEnqueueError> {
+            self.queue.enqueue(bio)
+        }
+
+        fn metadata(&self) -> BlockDeviceMeta {
+            BlockDeviceMeta {
+                max_nr_segments_per_bio: self.queue.max_nr_segments_per_bio(),
+                nr_sectors: 1024,
+            }
+        }
+    }
+
+    #[ktest]
+    fn back_merge() {
+        let device = QueueDevice::new(QueueLimits::default());
+        device.submit(BioType::Write, 0, 1);
+        device.submit(BioType::Write, 8, 2);
+
+        assert_eq!(device.queue.num_requests(), 1);
+        assert_eq!(device.dequeue(), (BioType::Write, 0..24, 2));
+    }
+
+    #[ktest]
+    fn front_merge() {
+        let device = QueueDevice::new(QueueLimits::default());
+        device.submit(BioType::Write, 8, 1);
+        device.submit(BioType::Write, 0, 1);
+
+        assert_eq!(device.queue.num_requests(), 1);
+        let request = device.queue.dequeue();
+        let starts: Vec<_> = request
+            .bios()
+            .map(|bio| bio.sid_range().start.to_raw())
+            .collect();
+        assert_eq!(starts, vec![0, 8]);
+    }
+
+    #[ktest]
+    fn coalescing_merge() {
+        let device = QueueDevice::new(QueueLimits::default());
+        device.submit(BioType::Write, 0, 1);
+        device.submit(BioType::Write, 16, 1);
+        assert_eq!(device.queue.num_requests(), 2);
+
+        // The bio fills the gap, so the two requests become one.
+        device.submit(BioType::Write, 8, 1);
+        assert_eq!(device.queue.num_requests(), 1);
+        assert_eq!(device.dequeue(), (BioType::Write, 0..24, 3));
+    }
+
+    #[ktest]
+    fn no_merge_across_types_or_limits() {
+        let device = QueueDevice::new(QueueLimits {
+            max_nr_segments: 2,
+            max_nr_sectors: usize::MAX,
+        });
+        device.submit(BioType::Write, 0, 1);
+        device.submit(BioType::Read, 8, 1);
+        device.submit(BioType::Write, 16, 1);
+        device.submit(BioType::Write, 24, 1);
+        device.submit(BioType::Write, 32, 1);
+
+        assert_eq!(device.queue.num_requests(), 4);
+        assert_eq!(device.dequeue(), (BioType::Write, 0..8, 1));
+        assert_eq!(device.dequeue(), (BioType::Read, 8..16, 1));
+        assert_eq!(device.dequeue(), (BioType::Write, 16..32, 2));
+        assert_eq!(device.dequeue(), (BioType::Write, 32..40, 1));
+    }
+
+    #[ktest]
+    fn split_on_enqueue() {
+        let device = QueueDevice::new(QueueLimits {
+            max_nr_segments: usize::MAX,
+            max_nr_sectors: 16,
+        });
+        device.submit(BioType::Write, 0, 3);
+
+        assert_eq!(device.queue.num_requests(), 2);
+        assert_eq!(device.dequeue(), (BioType::Write, 0..16, 1));
+        assert_eq!(device.dequeue(), (BioType::Write, 16..24, 1));
+    }
+
+    #[ktest]
+    fn dispatch_in_sector_order() {
+        let device = QueueDevice::new(QueueLimits::default());
+        device.submit(BioType::Write, 64, 1);
+        device.submit(BioType::Write, 32, 1);
+        assert_eq!(device.dequeue(), (BioType::Write, 32..40, 1));
+
+        // The sweep continues from the last dispatched request and then wraps around.
+        device.submit(BioType::Write, 0, 1);
+        assert_eq!(device.dequeue(), (BioType::Write, 64..72, 1));
+        assert_eq!(device.dequeue(), (BioType::Write, 0..8, 1));
+    }
+
+    #[ktest]
+    fn expired_request_dispatched_first() {
+        let device = QueueDevice::new(QueueLimits::default());
+        device.submit(BioType::Write, 64, 1);
+        device.submit(BioType::Write, 0, 1);
+
+        {
+            let mut scheduler = device.queue.scheduler.lock();
+            let (seq, start) = scheduler
+                .fifo
+                .first_key_value()
+                .map(|(seq, start)| (*seq, *start))
+                .unwrap();
+            assert_eq!(start, 64);
+            scheduler.sorted.get_mut(&(start, seq)).unwrap().deadline = Duration::ZERO;
+        }
+
+        assert_eq!(device.dequeue(), (BioType::Write, 64..72, 1));
+        assert_eq!(device.dequeue(), (BioType::Write, 0..8, 1));
+    }
+
+    #[ktest]
+    fn flush_ordering() {
+        let device = QueueDevice::new(QueueLimits::default());
+        device.submit(BioType::Write, 16, 1);
+        device.submit(BioType::Write, 0, 1);
+        device.submit(BioType::Flush, 0, 0);
+        // The bios after the flush are not merged into the requests before it.
+        device.submit(BioType::Write, 8, 1);
+        device.submit(BioType::Write, 24, 1);
+        // But they are merged with each other.
+        device.submit(BioType::Write, 32, 1);
+
+        assert_eq!(device.queue.num_requests(), 5);
+        assert_eq!(device.dequeue(), (BioType::Write, 16..24, 1));
+        assert_eq!(device.dequeue(), (BioType::Write, 0..8, 1));
+        assert_eq!(device.dequeue(), (BioType::Flush, 0..0, 1));
+        assert_eq!(device.dequeue(), (BioType::Write, 8..16, 1));
+        assert_eq!(device.dequeue(), (BioType::Write, 24..40, 2));
+    }
+}
@@ -0,0 +1,103 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The governors, which choose the target frequencies of the policies.
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::CpufreqError;
+
+/// The utilization (in percent) above which the ondemand governor picks the maximum frequency.
+static UP_THRESHOLD: AtomicU32 = AtomicU32::new(80);
+/// The interval (in milliseconds) between two samples of the utilization.
+static SAMPLING_RATE_MS: AtomicU32 = AtomicU32::new(20);
+
+/// The minimum sampling rate (in milliseconds).
+pub const MIN_SAMPLING_RATE_MS: u32 = 10;
+
+/// A governor of the CPU frequency.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Governor {
+    /// Runs at the maximum frequency.
+    Performance,
+    /// Runs at the minimum frequency.
+    Powersave,
+    /// Scales the frequency with the utilization of the CPUs.
+    Ondemand,
+    /// Runs at the frequency set by the user.
+    Userspace,
+}
+
+impl Governor {
+    /// All the governors.
+    pub const ALL: [Governor; 4] = [
+        Governor::Performance,
+        Governor::Powersave,
+        Governor::Ondemand,
+        Governor::Userspace,
+    ];
+
+    /// Returns the name of the governor, which is the same as Linux.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Governor::Performance => "performance",
+            Governor::Powersave => "powersave",
+            Governor::Ondemand => "ondemand",
+            Governor::Userspace => "userspace",
+        }
+    }
+
+    /// Returns the governor with the name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|governor| governor.name() == name)
+    }
+
+    /// Returns the target frequency (in kHz) in the range of the policy.
+    ///
+    /// The utilization is the maximum utilization (in percent) of the CPUs in the policy, and
+    /// `setspeed` is the frequency set by the user.
+    pub(crate) fn target_freq(&self, min: u32, max: u32, utilization: u32, setspeed: u32) -> u32 {
+        match self {
+            Governor::Performance => max,
+            Governor::Powersave => min,
+            // Like the ondemand governor of Linux, the frequency jumps to the maximum if the
+            // CPUs are busy, and is proportional to the utilization otherwise.
+            Governor::Ondemand if utilization > UP_THRESHOLD.load(Ordering::Relaxed) => max,
+            Governor::Ondemand => min + ((max - min) as u64 * utilization as u64 / 100) as u32,
+            Governor::Userspace => setspeed.clamp(min, max),
+        }
+    }
+}
+
+/// Returns the utilization threshold (in percent) of the ondemand governor.
+pub fn up_threshold() -> u32 {
+    UP_THRESHOLD.load(Ordering::Relaxed)
+}
+
+/// Sets the utilization threshold (in percent) of the ondemand governor.
+///
+/// The threshold must be in `1..=100`.
+pub fn set_up_threshold(threshold: u32) -> Result<(), CpufreqError> {
+    if !(1..=100).contains(&threshold) {
+        return Err(CpufreqError::InvalidArgs);
+    }
+    UP_THRESHOLD.store(threshold, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Returns the interval (in milliseconds) between two samples of the utilization.
+pub fn sampling_rate_ms() -> u32 {
+    SAMPLING_RATE_MS.load(Ordering::Relaxed)
+}
+
+/// Sets the interval (in milliseconds) between two samples of the utilization.
+///
+/// The rate cannot be shorter than [`MIN_SAMPLING_RATE_MS`].
+pub fn set_sampling_rate_ms(rate: u32) -> Result<(), CpufreqError> {
+    if rate < MIN_SAMPLING_RATE_MS {
+        return Err(CpufreqError::InvalidArgs);
+    }
+    SAMPLING_RATE_MS.store(rate, Ordering::Relaxed);
+    Ok(())
+}
@@ -0,0 +1,154 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The CPU frequency scaling subsystem of Asterinas.
+//!
+//! The CPUs that share a clock form a performance domain, whose operating performance points
+//! (OPPs) are described by the firmware or the device tree. Each domain is managed by a
+//! [`CpufreqPolicy`], which changes the frequency with a [`CpufreqDriver`] as chosen by its
+//! [`Governor`]. The kernel reports the utilization of the CPUs periodically (see
+//! [`update_utilization`]), which drives the ondemand governor.
+//!
+//! The following drivers are provided:
+//!
+//! - `scmi`: the performance domains managed by an SCMI platform agent, which are discovered
+//!   through the device tree.
+//!
+//! The SoC-specific drivers can be registered by [`register_driver`] with the domains from
+//! the OPP tables in the device tree (see [`dt_perf_domains`]).
+#![no_std]
+#![deny(unsafe_code)]
+
+extern crate alloc;
+
+mod governor;
+#[cfg(target_arch = "riscv64")]
+mod opp;
+mod policy;
+#[cfg(target_arch = "riscv64")]
+mod scmi;
+
+use alloc::{sync::Arc, vec::Vec};
+use core::fmt::Debug;
+
+use component::{init_component, ComponentInitError};
+use log::info;
+use ostd::{
+    cpu::CpuId,
+    sync::{LocalIrqDisabled, SpinLock},
+};
+
+#[cfg(target_arch = "riscv64")]
+pub use self::opp::dt_perf_domains;
+pub use self::{
+    governor::{
+        sampling_rate_ms, set_sampling_rate_ms, set_up_threshold, up_threshold, Governor,
+        MIN_SAMPLING_RATE_MS,
+    },
+    policy::{CpufreqPolicy, PerfDomain},
+};
+
+static POLICIES: SpinLock<Vec<Arc<CpufreqPolicy>>, LocalIrqDisabled> = SpinLock::new(Vec::new());
+
+#[init_component]
+fn cpufreq_component_init() -> Result<(), ComponentInitError> {
+    #[cfg(target_arch = "riscv64")]
+    if let Some((driver, domains)) = scmi::probe() {
+        register_driver(driver, domains);
+    }
+    Ok(())
+}
+
+/// An operating performance point (OPP).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Opp {
+    /// The frequency in kHz
+    pub freq_khz: u32,
+    /// The voltage in uV, if known
+    pub microvolt: Option<u32>,
+    /// Whether the OPP is only used when boosting
+    pub is_turbo: bool,
+}
+
+/// The errors of CPU frequency drivers.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CpufreqError {
+    /// The parameters are invalid.
+    InvalidArgs,
+    /// The hardware or the firmware does not respond in time.
+    Busy,
+    /// The hardware or the firmware reports an error.
+    IoError,
+}
+
+/// A CPU frequency driver.
+///
+/// The methods may be called in the interrupt context, so they must not sleep.
+pub trait CpufreqDriver: Send + Sync + Debug {
+    /// Returns the name of the driver.
+    fn name(&self) -> &str;
+
+    /// Sets the frequency of the domain to the OPP, which is one of the OPPs of the domain.
+    fn set_target(&self, domain: usize, opp: &Opp) -> Result<(), CpufreqError>;
+
+    /// Returns the current frequency (in kHz) of the domain, if it can be read from the
+    /// hardware.
+    fn current_freq(&self, _domain: usize) -> Option<u32> {
+        None
+    }
+}
+
+/// Registers a driver with the performance domains that it manages.
+///
+/// A policy is created for each domain. The domains that contain the CPUs that have been
+/// managed by other policies are ignored.
+pub fn register_driver(driver: Arc<dyn CpufreqDriver>, domains: Vec<PerfDomain>) {
+    for domain in domains {
+        let mut policies = POLICIES.lock();
+        if policies
+            .iter()
+            .any(|policy| domain.cpus.iter().any(|cpu| policy.cpus().contains(cpu)))
+        {
+            continue;
+        }
+
+        let policy = Arc::new(CpufreqPolicy::new(policies.len(), domain, driver.clone()));
+        info!(
+            "[Cpufreq]: registered policy{} of {} with {}-{} kHz",
+            policy.index(),
+            driver.name(),
+            policy.cpuinfo_min_freq(),
+            policy.cpuinfo_max_freq()
+        );
+        policies.push(policy);
+    }
+}
+
+/// Returns all the policies.
+pub fn all_policies() -> Vec<Arc<CpufreqPolicy>> {
+    POLICIES.lock().clone()
+}
+
+/// Returns the policy with the index.
+pub fn get_policy(index: usize) -> Option<Arc<CpufreqPolicy>> {
+    POLICIES.lock().get(index).cloned()
+}
+
+/// Returns the policy that manages the CPU.
+pub fn policy_of(cpu: CpuId) -> Option<Arc<CpufreqPolicy>> {
+    POLICIES
+        .lock()
+        .iter()
+        .find(|policy| policy.cpus().contains(cpu))
+        .cloned()
+}
+
+/// Updates the utilization (in percent) of the CPUs, which is returned by the closure.
+///
+/// The utilization of a policy is the maximum utilization of its CPUs. This function should
+/// be called every [`sampling_rate_ms`] milliseconds.
+pub fn update_utilization(utilization_of: impl Fn(CpuId) -> u32) {
+    for policy in all_policies() {
+        let utilization = policy.cpus().iter().map(&utilization_of).max().unwrap_or(0);
+        policy.update_utilization(utilization);
+    }
+}
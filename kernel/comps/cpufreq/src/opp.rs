@@ -0,0 +1,144 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The operating performance points (OPPs) in the device tree.
+//!
+//! Both bindings of Linux are supported:
+//!
+//! - `operating-points-v2`: the property of a CPU node is the phandle of an OPP table, whose
+//!   children are the OPPs with `opp-hz`, `opp-microvolt`, etc. The CPUs that refer to the
+//!   same table with `opp-shared` share the clock, so they are in the same domain.
+//! - `operating-points`: the legacy property of a CPU node, which is a list of pairs of the
+//!   frequency (in kHz) and the voltage (in uV). Each CPU is in its own domain.
+//!
+//! Reference: <https://www.kernel.org/doc/Documentation/devicetree/bindings/opp/opp-v2.yaml>
+
+use alloc::vec::Vec;
+
+use fdt::node::FdtNode;
+use ostd::{
+    arch::{boot::DEVICE_TREE, hart},
+    cpu::{CpuId, CpuSet},
+};
+
+use crate::{Opp, PerfDomain};
+
+/// Returns the performance domains that are described by the OPPs in the device tree.
+///
+/// The domains can be passed to [`crate::register_driver`] by the SoC-specific drivers, which
+/// change the frequencies with the clock controllers of the SoCs. The IDs of the domains are
+/// their indexes in the returned vector.
+pub fn dt_perf_domains() -> Vec<PerfDomain> {
+    let device_tree = DEVICE_TREE.get().unwrap();
+    let mut domains: Vec<PerfDomain> = Vec::new();
+    // The phandles of the shared tables, indexed by the domains.
+    let mut shared_tables: Vec<Option<u32>> = Vec::new();
+
+    for node in device_tree
+        .all_nodes()
+        .filter(|node| node.name.starts_with("cpu@"))
+    {
+        let Some(cpu) = cpu_of_node(&node) else {
+            continue;
+        };
+
+        if let Some(phandle) = node
+            .property("operating-points-v2")
+            .and_then(|prop| prop.as_usize())
+        {
+            let phandle = phandle as u32;
+            if let Some(index) = shared_tables.iter().position(|t| *t == Some(phandle)) {
+                domains[index].cpus.add(cpu);
+                continue;
+            }
+            let Some(table) = device_tree.find_phandle(phandle) else {
+                continue;
+            };
+            let Some(domain) = parse_opp_table(domains.len(), &table, cpu) else {
+                continue;
+            };
+            let is_shared = table.property("opp-shared").is_some();
+            shared_tables.push(is_shared.then_some(phandle));
+            domains.push(domain);
+        } else if let Some(domain) = parse_legacy_opps(domains.len(), &node, cpu) {
+            shared_tables.push(None);
+            domains.push(domain);
+        }
+    }
+
+    domains
+}
+
+/// Returns the CPU whose hart ID is the `reg` property of the CPU node.
+pub(crate) fn cpu_of_node(node: &FdtNode) -> Option<CpuId> {
+    let hart_id = node.reg()?.next()?.starting_address as usize;
+    hart::cpu_id(hart_id)
+}
+
+fn parse_opp_table(id: usize, table: &FdtNode, cpu: CpuId) -> Option<PerfDomain> {
+    let mut opps = Vec::new();
+    let mut transition_latency_ns = 0;
+
+    for child in table.children() {
+        if child
+            .property("status")
+            .and_then(|prop| prop.as_str())
+            .is_some_and(|status| status != "okay" && status != "ok")
+        {
+            continue;
+        }
+        let Some(hz) = child
+            .property("opp-hz")
+            .and_then(|prop| read_be_u64(prop.value))
+        else {
+            continue;
+        };
+        let microvolt = child
+            .property("opp-microvolt")
+            .and_then(|prop| read_be_u32(prop.value, 0));
+        if let Some(latency) = child
+            .property("clock-latency-ns")
+            .and_then(|prop| read_be_u32(prop.value, 0))
+        {
+            transition_latency_ns = transition_latency_ns.max(latency);
+        }
+
+        opps.push(Opp {
+            freq_khz: (hz / 1000) as u32,
+            microvolt,
+            is_turbo: child.property("turbo-mode").is_some(),
+        });
+    }
+
+    PerfDomain::new(id, CpuSet::from(cpu), opps, transition_latency_ns)
+}
+
+fn parse_legacy_opps(id: usize, node: &FdtNode, cpu: CpuId) -> Option<PerfDomain> {
+    let value = node.property("operating-points")?.value;
+    let opps = (0..value.len() / 8)
+        .filter_map(|index| {
+            Some(Opp {
+                freq_khz: read_be_u32(value, index * 2)?,
+                microvolt: read_be_u32(value, index * 2 + 1),
+                is_turbo: false,
+            })
+        })
+        .collect();
+    let transition_latency_ns = node
+        .property("clock-latency")
+        .and_then(|prop| read_be_u32(prop.value, 0))
+        .unwrap_or(0);
+
+    PerfDomain::new(id, CpuSet::from(cpu), opps, transition_latency_ns)
+}
+
+/// Reads the big-endian `u32` at the index of the cells.
+pub(crate) fn read_be_u32(value: &[u8], index: usize) -> Option<u32> {
+    let bytes = value.get(index * 4..index * 4 + 4)?;
+    Some(u32::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+/// Reads the big-endian `u64` at the beginning of the value.
+fn read_be_u64(value: &[u8]) -> Option<u64> {
+    let bytes = value.get(0..8)?;
+    Some(u64::from_be_bytes(bytes.try_into().unwrap()))
+}
@@ -0,0 +1,315 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The policies of the CPU frequency.
+
+use alloc::{sync::Arc, vec::Vec};
+
+use log::warn;
+use ostd::{
+    cpu::CpuSet,
+    sync::{LocalIrqDisabled, SpinLock},
+};
+
+use crate::{CpufreqDriver, CpufreqError, Governor, Opp};
+
+/// The governor of a new policy.
+const DEFAULT_GOVERNOR: Governor = Governor::Ondemand;
+
+/// A performance domain, i.e., a group of CPUs that share the frequency.
+#[derive(Clone, Debug)]
+pub struct PerfDomain {
+    /// The ID of the domain in the driver
+    pub(crate) id: usize,
+    pub(crate) cpus: CpuSet,
+    /// The OPPs in the ascending order of the frequencies
+    pub(crate) opps: Vec<Opp>,
+    pub(crate) transition_latency_ns: u32,
+}
+
+impl PerfDomain {
+    /// Creates a domain with the OPPs.
+    ///
+    /// This method returns `None` if there are no CPUs or no OPPs.
+    pub fn new(
+        id: usize,
+        cpus: CpuSet,
+        mut opps: Vec<Opp>,
+        transition_latency_ns: u32,
+    ) -> Option<Self> {
+        opps.retain(|opp| opp.freq_khz > 0);
+        opps.sort_by_key(|opp| opp.freq_khz);
+        opps.dedup_by_key(|opp| opp.freq_khz);
+        if cpus.count() == 0 || opps.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            id,
+            cpus,
+            opps,
+            transition_latency_ns,
+        })
+    }
+}
+
+/// A policy of the CPU frequency, which manages the frequency of a performance domain.
+///
+/// Like Linux, the frequency is chosen by the governor of the policy, within the limits set by
+/// the user (i.e., `scaling_min_freq` and `scaling_max_freq`).
+#[derive(Debug)]
+pub struct CpufreqPolicy {
+    index: usize,
+    domain: PerfDomain,
+    driver: Arc<dyn CpufreqDriver>,
+    state: SpinLock<PolicyState, LocalIrqDisabled>,
+}
+
+#[derive(Debug)]
+struct PolicyState {
+    governor: Governor,
+    /// The minimum frequency (in kHz) set by the user
+    min_khz: u32,
+    /// The maximum frequency (in kHz) set by the user
+    max_khz: u32,
+    /// The maximum frequency (in kHz) allowed by the thermal framework
+    thermal_max_khz: u32,
+    /// The current frequency (in kHz)
+    cur_khz: u32,
+    /// The frequency (in kHz) set by the user for the userspace governor
+    setspeed_khz: u32,
+    /// The last utilization (in percent) of the CPUs
+    utilization: u32,
+    /// The number of the frequency transitions
+    nr_transitions: u64,
+}
+
+impl CpufreqPolicy {
+    pub(crate) fn new(index: usize, domain: PerfDomain, driver: Arc<dyn CpufreqDriver>) -> Self {
+        let min_khz = domain.opps.first().unwrap().freq_khz;
+        // The turbo OPPs are not used by default.
+        let max_khz = domain
+            .opps
+            .iter()
+            .rev()
+            .find(|opp| !opp.is_turbo)
+            .unwrap_or(domain.opps.last().unwrap())
+            .freq_khz;
+        let cur_khz = driver.current_freq(domain.id).unwrap_or(0);
+
+        let policy = Self {
+            index,
+            domain,
+            driver,
+            state: SpinLock::new(PolicyState {
+                governor: DEFAULT_GOVERNOR,
+                min_khz,
+                max_khz,
+                thermal_max_khz: u32::MAX,
+                cur_khz,
+                setspeed_khz: cur_khz,
+                utilization: 0,
+                nr_transitions: 0,
+            }),
+        };
+        policy.apply_governor(&mut policy.state.lock());
+        policy
+    }
+
+    /// Returns the index of the policy.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the name of the driver.
+    pub fn driver_name(&self) -> &str {
+        self.driver.name()
+    }
+
+    /// Returns the CPUs that are managed by the policy.
+    pub fn cpus(&self) -> &CpuSet {
+        &self.domain.cpus
+    }
+
+    /// Returns the OPPs in the ascending order of the frequencies.
+    pub fn opps(&self) -> &[Opp] {
+        &self.domain.opps
+    }
+
+    /// Returns the minimum frequency (in kHz) supported by the hardware.
+    pub fn cpuinfo_min_freq(&self) -> u32 {
+        self.domain.opps.first().unwrap().freq_khz
+    }
+
+    /// Returns the maximum frequency (in kHz) supported by the hardware.
+    pub fn cpuinfo_max_freq(&self) -> u32 {
+        self.domain.opps.last().unwrap().freq_khz
+    }
+
+    /// Returns the time (in nanoseconds) to switch between two frequencies.
+    pub fn transition_latency_ns(&self) -> u32 {
+        self.domain.transition_latency_ns
+    }
+
+    /// Returns the governor.
+    pub fn governor(&self) -> Governor {
+        self.state.lock().governor
+    }
+
+    /// Sets the governor, which chooses a new frequency immediately.
+    pub fn set_governor(&self, governor: Governor) {
+        let mut state = self.state.lock();
+        if governor == Governor::Userspace && state.governor != Governor::Userspace {
+            state.setspeed_khz = state.cur_khz;
+        }
+        state.governor = governor;
+        self.apply_governor(&mut state);
+    }
+
+    /// Returns the current frequency (in kHz).
+    pub fn cur_freq(&self) -> u32 {
+        self.state.lock().cur_khz
+    }
+
+    /// Returns the frequency (in kHz) read from the hardware, if the driver supports it.
+    pub fn hw_cur_freq(&self) -> Option<u32> {
+        self.driver.current_freq(self.domain.id)
+    }
+
+    /// Returns the minimum frequency (in kHz) set by the user.
+    pub fn min_freq(&self) -> u32 {
+        self.state.lock().min_khz
+    }
+
+    /// Returns the maximum frequency (in kHz) set by the user.
+    pub fn max_freq(&self) -> u32 {
+        self.state.lock().max_khz
+    }
+
+    /// Sets the minimum frequency (in kHz).
+    ///
+    /// The frequency is clamped to the range of the hardware. If it is above the maximum
+    /// frequency, the maximum frequency is raised to it.
+    pub fn set_min_freq(&self, freq_khz: u32) {
+        let freq_khz = freq_khz.clamp(self.cpuinfo_min_freq(), self.cpuinfo_max_freq());
+        let mut state = self.state.lock();
+        state.min_khz = freq_khz;
+        state.max_khz = state.max_khz.max(freq_khz);
+        self.apply_governor(&mut state);
+    }
+
+    /// Sets the maximum frequency (in kHz).
+    ///
+    /// The frequency is clamped to the range of the hardware. If it is below the minimum
+    /// frequency, the minimum frequency is lowered to it.
+    pub fn set_max_freq(&self, freq_khz: u32) {
+        let freq_khz = freq_khz.clamp(self.cpuinfo_min_freq(), self.cpuinfo_max_freq());
+        let mut state = self.state.lock();
+        state.max_khz = freq_khz;
+        state.min_khz = state.min_khz.min(freq_khz);
+        self.apply_governor(&mut state);
+    }
+
+    /// Returns the maximum frequency (in kHz) allowed by the thermal framework, if limited.
+    pub fn thermal_limit(&self) -> Option<u32> {
+        Some(self.state.lock().thermal_max_khz).filter(|limit| *limit != u32::MAX)
+    }
+
+    /// Limits the frequency (in kHz) to cool the CPUs down, or removes the limit with `None`.
+    ///
+    /// The limit takes precedence over the limits set by the user.
+    pub fn set_thermal_limit(&self, freq_khz: Option<u32>) {
+        let mut state = self.state.lock();
+        state.thermal_max_khz = freq_khz.unwrap_or(u32::MAX);
+        self.apply_governor(&mut state);
+    }
+
+    /// Returns the frequency (in kHz) set by the user for the userspace governor.
+    pub fn setspeed(&self) -> u32 {
+        self.state.lock().setspeed_khz
+    }
+
+    /// Sets the frequency (in kHz) for the userspace governor.
+    ///
+    /// This method fails with [`CpufreqError::InvalidArgs`] if the governor is not the
+    /// userspace governor.
+    pub fn set_setspeed(&self, freq_khz: u32) -> Result<(), CpufreqError> {
+        let mut state = self.state.lock();
+        if state.governor != Governor::Userspace {
+            return Err(CpufreqError::InvalidArgs);
+        }
+        state.setspeed_khz = freq_khz;
+        self.apply_governor(&mut state);
+        Ok(())
+    }
+
+    /// Returns the number of the frequency transitions.
+    pub fn nr_transitions(&self) -> u64 {
+        self.state.lock().nr_transitions
+    }
+
+    /// Updates the utilization (in percent) of the CPUs, and lets the governor choose the
+    /// frequency.
+    pub(crate) fn update_utilization(&self, utilization: u32) {
+        let mut state = self.state.lock();
+        state.utilization = utilization.min(100);
+        if state.governor == Governor::Ondemand {
+            self.apply_governor(&mut state);
+        }
+    }
+
+    fn apply_governor(&self, state: &mut PolicyState) {
+        let (min_khz, max_khz) = state.limits();
+        let target =
+            state
+                .governor
+                .target_freq(min_khz, max_khz, state.utilization, state.setspeed_khz);
+        let Some(opp) = self.select_opp(state, target) else {
+            return;
+        };
+        if opp.freq_khz == state.cur_khz {
+            return;
+        }
+
+        match self.driver.set_target(self.domain.id, opp) {
+            Ok(()) => {
+                state.cur_khz = opp.freq_khz;
+                state.nr_transitions += 1;
+            }
+            Err(err) => warn!(
+                "[Cpufreq]: policy{}: cannot set the frequency to {} kHz: {:?}",
+                self.index, opp.freq_khz, err
+            ),
+        }
+    }
+
+    /// Returns the OPP with the lowest frequency at or above the target, within the limits.
+    ///
+    /// If there are no OPPs within the limits (e.g., the thermal limit is below all the OPPs),
+    /// the highest OPP below the maximum frequency, or the lowest OPP, is returned.
+    fn select_opp(&self, state: &PolicyState, target_khz: u32) -> Option<&Opp> {
+        let (min_khz, max_khz) = state.limits();
+        let mut in_limits = self
+            .domain
+            .opps
+            .iter()
+            .filter(|opp| (min_khz..=max_khz).contains(&opp.freq_khz));
+        in_limits
+            .clone()
+            .find(|opp| opp.freq_khz >= target_khz)
+            .or_else(|| in_limits.next_back())
+            .or_else(|| {
+                let mut opps = self.domain.opps.iter();
+                opps.clone()
+                    .rfind(|opp| opp.freq_khz <= max_khz)
+                    .or_else(|| opps.next())
+            })
+    }
+}
+
+impl PolicyState {
+    /// Returns the effective minimum and maximum frequencies (in kHz).
+    fn limits(&self) -> (u32, u32) {
+        let max_khz = self.max_khz.min(self.thermal_max_khz);
+        (self.min_khz.min(max_khz), max_khz)
+    }
+}
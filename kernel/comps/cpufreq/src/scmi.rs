@@ -0,0 +1,303 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The CPU frequency driver with the performance domain management protocol of SCMI.
+//!
+//! The System Control and Management Interface (SCMI) is implemented by a platform agent (e.g.,
+//! the firmware on a management core), which manages the clocks and the voltages of the
+//! performance domains. The performance levels of a domain are described by the agent, and a
+//! level is requested by sending a message.
+//!
+//! The messages are exchanged through a shared memory channel (`arm,scmi-shmem`). Doorbells
+//! (i.e., the mailboxes) are not supported, so the platform agent must poll the channel, and
+//! the completion of a message is polled by the driver as well.
+//!
+//! Reference: <https://developer.arm.com/documentation/den0056/latest>
+
+use alloc::{string::String, sync::Arc, vec, vec::Vec};
+use core::hint::spin_loop;
+
+use fdt::node::FdtNode;
+use log::{info, warn};
+use ostd::{
+    arch::boot::DEVICE_TREE,
+    cpu::CpuSet,
+    io::MmioRegion,
+    sync::{LocalIrqDisabled, SpinLock},
+};
+
+use crate::{
+    opp::{cpu_of_node, read_be_u32},
+    CpufreqDriver, CpufreqError, Opp, PerfDomain,
+};
+
+const SCMI_COMPATIBLES: &[&str] = &["arm,scmi"];
+
+/// The ID of the performance domain management protocol.
+const PROTOCOL_PERF: u8 = 0x13;
+const MSG_PROTOCOL_ATTRIBUTES: u8 = 0x1;
+const MSG_DOMAIN_ATTRIBUTES: u8 = 0x3;
+const MSG_DESCRIBE_LEVELS: u8 = 0x4;
+const MSG_LEVEL_SET: u8 = 0x7;
+const MSG_LEVEL_GET: u8 = 0x8;
+
+// The layout of the shared memory channel.
+const SHMEM_CHANNEL_STATUS: usize = 0x4;
+const SHMEM_FLAGS: usize = 0x10;
+const SHMEM_LENGTH: usize = 0x14;
+const SHMEM_MSG_HEADER: usize = 0x18;
+const SHMEM_PAYLOAD: usize = 0x1c;
+
+const CHANNEL_STATUS_FREE: u32 = 1 << 0;
+const CHANNEL_STATUS_ERROR: u32 = 1 << 1;
+
+/// The number of polls before a message is considered timed out.
+const MAX_POLLS: usize = 10_000_000;
+
+/// The SCMI performance driver.
+#[derive(Debug)]
+pub(crate) struct ScmiPerf {
+    name: String,
+    channel: SpinLock<Channel, LocalIrqDisabled>,
+    /// The pairs of the frequencies (in kHz) and the performance levels, indexed by domains.
+    levels: Vec<Vec<(u32, u32)>>,
+}
+
+#[derive(Debug)]
+struct Channel {
+    shmem: MmioRegion,
+    next_token: u32,
+}
+
+/// Probes the SCMI agent in the device tree, and returns the driver with its domains.
+pub(crate) fn probe() -> Option<(Arc<ScmiPerf>, Vec<PerfDomain>)> {
+    let device_tree = DEVICE_TREE.get().unwrap();
+    let node = device_tree.all_nodes().find(|node| {
+        node.compatible()
+            .is_some_and(|compatible| compatible.all().any(|c| SCMI_COMPATIBLES.contains(&c)))
+    })?;
+    let perf_node = node.children().find(|child| {
+        child
+            .reg()
+            .and_then(|mut reg| reg.next())
+            .is_some_and(|reg| reg.starting_address as usize == PROTOCOL_PERF as usize)
+    })?;
+
+    let shmem_node = node
+        .property("shmem")
+        .and_then(|prop| prop.as_usize())
+        .and_then(|phandle| device_tree.find_phandle(phandle as u32))?;
+    let shmem = match MmioRegion::claim_fdt_reg(&shmem_node) {
+        Ok(shmem) => shmem,
+        Err(err) => {
+            warn!(
+                "[Cpufreq]: {}: cannot claim the shared memory: {:?}",
+                node.name, err
+            );
+            return None;
+        }
+    };
+    let mut channel = Channel {
+        shmem,
+        next_token: 0,
+    };
+
+    let mut response = [0u32; 1];
+    channel
+        .call(MSG_PROTOCOL_ATTRIBUTES, &[], &mut response)
+        .ok()?;
+    let nr_domains = (response[0] & 0xffff) as usize;
+
+    let mut levels = Vec::with_capacity(nr_domains);
+    let mut domains = Vec::with_capacity(nr_domains);
+    for domain in 0..nr_domains {
+        let domain_levels = channel.describe_levels(domain as u32).unwrap_or_default();
+        let opps = domain_levels
+            .iter()
+            .map(|(freq_khz, _)| Opp {
+                freq_khz: *freq_khz,
+                microvolt: None,
+                is_turbo: false,
+            })
+            .collect();
+        let cpus = domain_cpus(&perf_node, domain as u32);
+        levels.push(domain_levels);
+        if let Some(domain) = PerfDomain::new(domain, cpus, opps, 0) {
+            domains.push(domain);
+        }
+    }
+
+    info!(
+        "[Cpufreq]: found SCMI agent {} with {} performance domains",
+        node.name, nr_domains
+    );
+    let driver = Arc::new(ScmiPerf {
+        name: String::from(node.name),
+        channel: SpinLock::new(channel),
+        levels,
+    });
+    Some((driver, domains))
+}
+
+/// Returns the CPUs in the domain, which refer to the domain by `clocks` or `power-domains`.
+fn domain_cpus(perf_node: &FdtNode, domain: u32) -> CpuSet {
+    let mut cpus = CpuSet::new_empty();
+    let Some(phandle) = perf_node
+        .property("phandle")
+        .and_then(|prop| prop.as_usize())
+    else {
+        return cpus;
+    };
+
+    for node in DEVICE_TREE
+        .get()
+        .unwrap()
+        .all_nodes()
+        .filter(|node| node.name.starts_with("cpu@"))
+    {
+        let refers_to_domain = ["clocks", "power-domains"].iter().any(|name| {
+            node.property(name).is_some_and(|prop| {
+                read_be_u32(prop.value, 0) == Some(phandle as u32)
+                    && read_be_u32(prop.value, 1) == Some(domain)
+            })
+        });
+        if let Some(cpu) = cpu_of_node(&node).filter(|_| refers_to_domain) {
+            cpus.add(cpu);
+        }
+    }
+    cpus
+}
+
+impl Channel {
+    /// Sends a message of the performance protocol, and waits for the response.
+    ///
+    /// The response is written to the buffer, excluding the status.
+    fn call(
+        &mut self,
+        message: u8,
+        payload: &[u32],
+        response: &mut [u32],
+    ) -> Result<(), CpufreqError> {
+        if !self.poll_free() {
+            return Err(CpufreqError::Busy);
+        }
+
+        let token = self.next_token;
+        self.next_token = (self.next_token + 1) & 0x3ff;
+        let header = message as u32 | ((PROTOCOL_PERF as u32) << 10) | (token << 18);
+        for (index, word) in payload.iter().enumerate() {
+            self.write(SHMEM_PAYLOAD + index * 4, *word)?;
+        }
+        self.write(SHMEM_LENGTH, 4 + payload.len() as u32 * 4)?;
+        self.write(SHMEM_MSG_HEADER, header)?;
+        self.write(SHMEM_FLAGS, 0)?;
+        // Hand the channel over to the platform agent.
+        self.write(SHMEM_CHANNEL_STATUS, 0)?;
+
+        if !self.poll_free() {
+            return Err(CpufreqError::Busy);
+        }
+        if self.read(SHMEM_CHANNEL_STATUS)? & CHANNEL_STATUS_ERROR != 0 {
+            return Err(CpufreqError::IoError);
+        }
+
+        // The response starts with the status, which is zero on success.
+        if self.read(SHMEM_PAYLOAD)? != 0 {
+            return Err(CpufreqError::IoError);
+        }
+        let nr_words = (self.read(SHMEM_LENGTH)? as usize).saturating_sub(8) / 4;
+        for (index, word) in response.iter_mut().take(nr_words).enumerate() {
+            *word = self.read(SHMEM_PAYLOAD + (index + 1) * 4)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the pairs of the frequencies and the performance levels of the domain.
+    fn describe_levels(&mut self, domain: u32) -> Result<Vec<(u32, u32)>, CpufreqError> {
+        // The sustained frequency (in kHz) and performance level give the frequency of the levels.
+        let mut attributes = [0u32; 4];
+        self.call(MSG_DOMAIN_ATTRIBUTES, &[domain], &mut attributes)?;
+        let (sustained_freq, sustained_level) = (attributes[2] as u64, attributes[3] as u64);
+        if sustained_level == 0 {
+            return Err(CpufreqError::InvalidArgs);
+        }
+
+        let mut levels = Vec::new();
+        let capacity = (self.shmem.length() - SHMEM_PAYLOAD) / 4;
+        let mut response = vec![0u32; capacity.saturating_sub(1)];
+        loop {
+            self.call(
+                MSG_DESCRIBE_LEVELS,
+                &[domain, levels.len() as u32],
+                &mut response,
+            )?;
+            let nr_returned = (response[0] & 0xfff) as usize;
+            let nr_remaining = response[0] >> 16;
+            // Each level is described by the level, the power cost, and the attributes.
+            for entry in response[1..].chunks_exact(3).take(nr_returned) {
+                let freq_khz = (entry[0] as u64 * sustained_freq / sustained_level) as u32;
+                levels.push((freq_khz, entry[0]));
+            }
+            if nr_remaining == 0 || nr_returned == 0 {
+                break;
+            }
+        }
+        Ok(levels)
+    }
+
+    fn poll_free(&self) -> bool {
+        for _ in 0..MAX_POLLS {
+            if self
+                .read(SHMEM_CHANNEL_STATUS)
+                .is_ok_and(|status| status & CHANNEL_STATUS_FREE != 0)
+            {
+                return true;
+            }
+            spin_loop();
+        }
+        false
+    }
+
+    fn read(&self, offset: usize) -> Result<u32, CpufreqError> {
+        self.shmem
+            .reg::<u32>(offset)
+            .map(|reg| reg.read())
+            .map_err(|_| CpufreqError::InvalidArgs)
+    }
+
+    fn write(&self, offset: usize, value: u32) -> Result<(), CpufreqError> {
+        self.shmem
+            .reg::<u32>(offset)
+            .map(|reg| reg.write(value))
+            .map_err(|_| CpufreqError::InvalidArgs)
+    }
+}
+
+impl CpufreqDriver for ScmiPerf {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn set_target(&self, domain: usize, opp: &Opp) -> Result<(), CpufreqError> {
+        let (_, level) = self
+            .levels
+            .get(domain)
+            .and_then(|levels| levels.iter().find(|(freq, _)| *freq == opp.freq_khz))
+            .ok_or(CpufreqError::InvalidArgs)?;
+        self.channel
+            .lock()
+            .call(MSG_LEVEL_SET, &[domain as u32, *level], &mut [])
+    }
+
+    fn current_freq(&self, domain: usize) -> Option<u32> {
+        let mut response = [0u32; 1];
+        self.channel
+            .lock()
+            .call(MSG_LEVEL_GET, &[domain as u32], &mut response)
+            .ok()?;
+        self.levels
+            .get(domain)?
+            .iter()
+            .find(|(_, level)| *level == response[0])
+            .map(|(freq, _)| *freq)
+    }
+}
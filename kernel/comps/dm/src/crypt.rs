@@ -0,0 +1,311 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The `crypt` target.
+//!
+//! The parameters are the same as those of Linux's dm-crypt:
+//!
+//! ```text
+//! <cipher> <key> <iv_offset> <device> <offset> [<#opt_params> <opt_params>]
+//! ```
+//!
+//! - `<cipher>` is `aes-xts-plain64` or `aes-xts-plain` (`capi:xts(aes)-plain64` and
+//!   `capi:xts(aes)-plain` are also accepted).
+//! - `<key>` is either the key in hexadecimal, or a key in the kernel keyring in the
+//!   form of `:<key_size>:<key_type>:<key_description>`.
+//! - `<iv_offset>` is added to the sector number to compute the initial vector.
+//! - `<device>` and `<offset>` are the same as those of the `linear` target.
+//! - The only optional parameter that changes the behavior is `allow_discards`.
+//!   The performance options of Linux (e.g., `same_cpu_crypt`) are accepted and ignored.
+//!
+//! Data is encrypted in units of 512-byte sectors. Writes are encrypted into bounce
+//! buffers before they are submitted, and reads are decrypted in place when they are
+//! completed.
+
+use alloc::{boxed::Box, format, string::String, sync::Arc, vec::Vec};
+
+use aster_block::{
+    bio::{BioDirection, BioEndIo, BioSegment, BioStatus, BioType, SubmittedBio},
+    id::Sid,
+    BlockDevice, SECTOR_SIZE,
+};
+use ostd::mm::VmIo;
+use spin::Once;
+
+use crate::{
+    table::{check_device_range, lookup_device, Target},
+    xts::Xts,
+    DmError,
+};
+
+/// A function that looks up a key in the kernel keyring by its type and description.
+pub type KeyLookupFn = fn(key_type: &str, description: &str) -> Option<Vec<u8>>;
+
+static KEY_LOOKUP: Once<KeyLookupFn> = Once::new();
+
+/// Sets the function to look up the keys in the kernel keyring.
+///
+/// Until this is called, only the keys in hexadecimal can be used.
+pub fn set_key_lookup(lookup: KeyLookupFn) {
+    KEY_LOOKUP.call_once(|| lookup);
+}
+
+/// The way to generate the initial vector from the sector number.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum IvMode {
+    /// The sector number truncated to 32 bits, in little endian.
+    Plain,
+    /// The sector number in 64 bits, in little endian.
+    Plain64,
+}
+
+impl IvMode {
+    fn iv(self, sector: u64) -> [u8; 16] {
+        let mut iv = [0u8; 16];
+        match self {
+            Self::Plain => iv[..4].copy_from_slice(&(sector as u32).to_le_bytes()),
+            Self::Plain64 => iv[..8].copy_from_slice(&sector.to_le_bytes()),
+        }
+        iv
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct CryptTarget {
+    cipher_spec: String,
+    key_spec: String,
+    cipher: Arc<Xts>,
+    iv_mode: IvMode,
+    iv_offset: u64,
+    device_path: String,
+    device: Arc<dyn BlockDevice>,
+    offset: u64,
+    opt_params: Vec<String>,
+    allow_discards: bool,
+}
+
+impl CryptTarget {
+    pub(crate) fn new(params: &str, len: u64) -> Result<Self, DmError> {
+        let args: Vec<&str> = params.split_ascii_whitespace().collect();
+        let [cipher_spec, key_spec, iv_offset, device_path, offset, opt_args @ ..] =
+            args.as_slice()
+        else {
+            return Err(DmError::InvalidArgs("invalid crypt target parameters"));
+        };
+
+        let iv_mode = match *cipher_spec {
+            "aes-xts-plain64" | "capi:xts(aes)-plain64" => IvMode::Plain64,
+            "aes-xts-plain" | "capi:xts(aes)-plain" => IvMode::Plain,
+            _ => return Err(DmError::InvalidArgs("unsupported cipher")),
+        };
+        let key = parse_key(key_spec)?;
+        let cipher = Xts::new(&key).ok_or(DmError::InvalidArgs("invalid key size"))?;
+        let iv_offset = iv_offset
+            .parse()
+            .map_err(|_| DmError::InvalidArgs("invalid crypt target iv offset"))?;
+        let offset = offset
+            .parse()
+            .map_err(|_| DmError::InvalidArgs("invalid crypt target offset"))?;
+
+        let mut opt_params = Vec::new();
+        let mut allow_discards = false;
+        if let [nr_opt_params, opt_args @ ..] = opt_args {
+            if nr_opt_params.parse::<usize>().ok() != Some(opt_args.len()) {
+                return Err(DmError::InvalidArgs(
+                    "invalid number of optional parameters",
+                ));
+            }
+            for opt_param in opt_args.iter().copied() {
+                match opt_param {
+                    "allow_discards" => allow_discards = true,
+                    "same_cpu_crypt"
+                    | "submit_from_crypt_cpus"
+                    | "no_read_workqueue"
+                    | "no_write_workqueue" => (),
+                    _ => return Err(DmError::InvalidArgs("unsupported optional parameter")),
+                }
+                opt_params.push(opt_param.into());
+            }
+        }
+
+        let device = lookup_device(device_path)?;
+        check_device_range(&device, offset, len)?;
+
+        Ok(Self {
+            cipher_spec: String::from(*cipher_spec),
+            key_spec: String::from(*key_spec),
+            cipher: Arc::new(cipher),
+            iv_mode,
+            iv_offset,
+            device_path: String::from(*device_path),
+            device,
+            offset,
+            opt_params,
+            allow_discards,
+        })
+    }
+
+    fn map_read(&self, bio: SubmittedBio, relative_start: u64) {
+        let cipher = self.cipher.clone();
+        let iv_mode = self.iv_mode;
+        let iv_start = relative_start + self.iv_offset;
+        let end_io: BioEndIo = Box::new(move |bio, status| {
+            if status != BioStatus::Complete {
+                return status;
+            }
+            match decrypt_in_place(&cipher, iv_mode, iv_start, bio.segments()) {
+                Ok(()) => status,
+                Err(_) => BioStatus::IoError,
+            }
+        });
+
+        let start_sid = Sid::new(self.offset + relative_start);
+        let segments = bio.segments().to_vec();
+        bio.redirect(start_sid, segments, Some(end_io))
+            .forward(self.device.as_ref());
+    }
+
+    fn map_write(&self, bio: SubmittedBio, relative_start: u64) {
+        let iv_start = relative_start + self.iv_offset;
+        let bounce_segments =
+            match encrypt_to_bounce(&self.cipher, self.iv_mode, iv_start, bio.segments()) {
+                Ok(bounce_segments) => bounce_segments,
+                Err(_) => {
+                    bio.complete(BioStatus::IoError);
+                    return;
+                }
+            };
+
+        let start_sid = Sid::new(self.offset + relative_start);
+        bio.redirect(start_sid, bounce_segments, None)
+            .forward(self.device.as_ref());
+    }
+}
+
+impl Target for CryptTarget {
+    fn type_name(&self) -> &'static str {
+        "crypt"
+    }
+
+    fn map(&self, bio: SubmittedBio, target_start: u64) {
+        let relative_start = bio.sid_range().start.to_raw() - target_start;
+        match bio.type_() {
+            BioType::Read => self.map_read(bio, relative_start),
+            BioType::Write => self.map_write(bio, relative_start),
+            BioType::Discard if self.allow_discards => {
+                let start_sid = Sid::new(self.offset + relative_start);
+                let segments = bio.segments().to_vec();
+                bio.redirect(start_sid, segments, None)
+                    .forward(self.device.as_ref());
+            }
+            BioType::Discard | BioType::Flush => bio.complete(BioStatus::NotSupported),
+        }
+    }
+
+    fn device(&self) -> &Arc<dyn BlockDevice> {
+        &self.device
+    }
+
+    fn params(&self) -> String {
+        let mut params = format!(
+            "{} {} {} {} {}",
+            self.cipher_spec, self.key_spec, self.iv_offset, self.device_path, self.offset
+        );
+        if !self.opt_params.is_empty() {
+            params += &format!(" {} {}", self.opt_params.len(), self.opt_params.join(" "));
+        }
+        params
+    }
+}
+
+/// Parses the key in hexadecimal, or looks up the key in the kernel keyring.
+fn parse_key(key_spec: &str) -> Result<Vec<u8>, DmError> {
+    if let Some(keyring_spec) = key_spec.strip_prefix(':') {
+        let mut fields = keyring_spec.splitn(3, ':');
+        let (Some(key_size), Some(key_type), Some(description)) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            return Err(DmError::InvalidArgs("invalid keyring key"));
+        };
+        let key_size: usize = key_size
+            .parse()
+            .map_err(|_| DmError::InvalidArgs("invalid key size"))?;
+        if key_type != "logon" && key_type != "user" {
+            return Err(DmError::InvalidArgs("unsupported key type"));
+        }
+
+        let lookup = KEY_LOOKUP.get().ok_or(DmError::NoKey)?;
+        let key = lookup(key_type, description).ok_or(DmError::NoKey)?;
+        if key.len() != key_size {
+            return Err(DmError::InvalidArgs("the key size does not match"));
+        }
+        return Ok(key);
+    }
+
+    if key_spec.len() % 2 != 0 {
+        return Err(DmError::InvalidArgs("invalid key"));
+    }
+    (0..key_spec.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&key_spec[i..i + 2], 16))
+        .collect::<Result<Vec<u8>, _>>()
+        .map_err(|_| DmError::InvalidArgs("invalid key"))
+}
+
+/// Encrypts the sectors in `segments` into newly allocated bounce segments.
+///
+/// The memory of `segments` is accessed by the CPU directly, since the segments of
+/// writes are mapped for the device to read only.
+fn encrypt_to_bounce(
+    cipher: &Xts,
+    iv_mode: IvMode,
+    mut sector: u64,
+    segments: &[BioSegment],
+) -> ostd::Result<Vec<BioSegment>> {
+    let mut buf = [0u8; SECTOR_SIZE];
+    let mut bounce_segments = Vec::with_capacity(segments.len());
+
+    for segment in segments {
+        let bounce_segment =
+            BioSegment::alloc(segment.nblocks(), BioDirection::ToDevice).slice(0, segment.nbytes());
+        let dma_slice = segment.inner_dma_slice();
+        for offset in (0..segment.nbytes()).step_by(SECTOR_SIZE) {
+            dma_slice
+                .stream()
+                .segment()
+                .read_bytes(dma_slice.offset() + offset, &mut buf)?;
+            cipher.encrypt(&iv_mode.iv(sector), &mut buf);
+            bounce_segment.write_bytes(offset, &buf)?;
+            sector += 1;
+        }
+        bounce_segments.push(bounce_segment);
+    }
+
+    Ok(bounce_segments)
+}
+
+/// Decrypts the sectors in `segments` in place.
+///
+/// The memory of `segments` is accessed by the CPU directly, since the segments of
+/// reads are mapped for the device to write only.
+fn decrypt_in_place(
+    cipher: &Xts,
+    iv_mode: IvMode,
+    mut sector: u64,
+    segments: &[BioSegment],
+) -> ostd::Result<()> {
+    let mut buf = [0u8; SECTOR_SIZE];
+
+    for segment in segments {
+        let dma_slice = segment.inner_dma_slice();
+        let memory = dma_slice.stream().segment();
+        for offset in (0..segment.nbytes()).step_by(SECTOR_SIZE) {
+            let offset = dma_slice.offset() + offset;
+            memory.read_bytes(offset, &mut buf)?;
+            cipher.decrypt(&iv_mode.iv(sector), &mut buf);
+            memory.write_bytes(offset, &buf)?;
+            sector += 1;
+        }
+    }
+
+    Ok(())
+}
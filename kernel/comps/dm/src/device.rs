@@ -0,0 +1,129 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::{format, string::String, sync::Arc};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use aster_block::{
+    bio::{BioEnqueueError, SubmittedBio},
+    BlockDevice, BlockDeviceMeta,
+};
+use ostd::sync::SpinLock;
+
+use crate::table::Table;
+
+/// A block device whose sectors are mapped to other block devices by a [`Table`].
+///
+/// Unlike Linux, the requests submitted while the device is suspended, or while it has no
+/// active table, are refused rather than deferred.
+#[derive(Debug)]
+pub struct MappedDevice {
+    name: String,
+    uuid: String,
+    minor: u32,
+    active: SpinLock<Option<Arc<Table>>>,
+    inactive: SpinLock<Option<Arc<Table>>>,
+    suspended: AtomicBool,
+}
+
+impl MappedDevice {
+    pub(crate) fn new(name: String, uuid: String, minor: u32) -> Self {
+        Self {
+            name,
+            uuid,
+            minor,
+            active: SpinLock::new(None),
+            inactive: SpinLock::new(None),
+            suspended: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns the name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the UUID, which may be empty.
+    pub fn uuid(&self) -> &str {
+        &self.uuid
+    }
+
+    /// Returns the minor number.
+    pub fn minor(&self) -> u32 {
+        self.minor
+    }
+
+    /// Returns the name of the device in `aster-block`.
+    pub fn block_device_name(&self) -> String {
+        format!("dm-{}", self.minor)
+    }
+
+    /// Returns the active table.
+    pub fn active_table(&self) -> Option<Arc<Table>> {
+        self.active.lock().clone()
+    }
+
+    /// Returns the inactive table.
+    pub fn inactive_table(&self) -> Option<Arc<Table>> {
+        self.inactive.lock().clone()
+    }
+
+    /// Loads `table` as the inactive table, replacing the previous inactive table (if any).
+    pub fn load_table(&self, table: Table) {
+        *self.inactive.lock() = Some(Arc::new(table));
+    }
+
+    /// Discards the inactive table.
+    pub fn clear_inactive_table(&self) {
+        *self.inactive.lock() = None;
+    }
+
+    /// Returns whether the device is suspended.
+    pub fn is_suspended(&self) -> bool {
+        self.suspended.load(Ordering::Acquire)
+    }
+
+    /// Suspends the device.
+    ///
+    /// The requests that have been submitted are not affected.
+    pub fn suspend(&self) {
+        self.suspended.store(true, Ordering::Release);
+    }
+
+    /// Resumes the device.
+    ///
+    /// If there is an inactive table, it becomes the active table.
+    pub fn resume(&self) {
+        if let Some(table) = self.inactive.lock().take() {
+            *self.active.lock() = Some(table);
+        }
+        self.suspended.store(false, Ordering::Release);
+    }
+}
+
+impl BlockDevice for MappedDevice {
+    fn enqueue(&self, bio: SubmittedBio) -> Result<(), BioEnqueueError> {
+        if self.is_suspended() {
+            return Err(BioEnqueueError::Refused);
+        }
+        let Some(table) = self.active_table() else {
+            return Err(BioEnqueueError::Refused);
+        };
+        table.map(bio)
+    }
+
+    fn metadata(&self) -> BlockDeviceMeta {
+        match self.active_table() {
+            Some(table) => BlockDeviceMeta {
+                max_nr_segments_per_bio: table.max_nr_segments_per_bio(),
+                nr_sectors: table.nr_sectors() as usize,
+            },
+            None => BlockDeviceMeta {
+                max_nr_segments_per_bio: usize::MAX,
+                nr_sectors: 0,
+            },
+        }
+    }
+
+    // Plugging is not forwarded to the underlying devices, since the active table may
+    // change between `plug` and `unplug`, which would leave the underlying devices plugged.
+}
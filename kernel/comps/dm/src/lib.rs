@@ -0,0 +1,132 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Device-mapper style stacking of block devices.
+//!
+//! A mapped device is a virtual block device whose sectors are mapped to the sectors of
+//! other block devices by a [`Table`]. Each entry (target) of the table maps a contiguous
+//! range of sectors in one of the following ways:
+//!
+//! - `linear`: to a contiguous range of sectors of another device.
+//! - `crypt`: to a contiguous range of sectors of another device, with the data encrypted
+//!   by AES-XTS.
+//!
+//! The table format follows the device mapper of Linux, so that the mapped devices can be
+//! configured from the user space (e.g., by `dmsetup` or `cryptsetup`) through the
+//! `/dev/mapper/control` device of the kernel. As in Linux, a table is first loaded as the
+//! inactive table of a mapped device, which replaces the active table when the device is
+//! resumed.
+//!
+//! Each mapped device is registered in `aster-block` as `dm-N`, where `N` is its minor
+//! number. The underlying devices are referred to by their names in `aster-block` (e.g.,
+//! `vda1` or `/dev/vda1`), or by the names of other mapped devices (e.g., `/dev/mapper/root`).
+#![no_std]
+#![deny(unsafe_code)]
+
+extern crate alloc;
+
+mod crypt;
+mod device;
+mod linear;
+mod table;
+mod xts;
+
+use alloc::{collections::BTreeMap, string::String, sync::Arc, vec::Vec};
+
+use ostd::sync::Mutex;
+
+pub use self::{
+    crypt::{set_key_lookup, KeyLookupFn},
+    device::MappedDevice,
+    table::{Table, Target, TargetSpec},
+};
+
+/// The maximum length of the name of a mapped device, including the final `\0` byte.
+pub const DM_NAME_LEN: usize = 128;
+
+/// The maximum length of the UUID of a mapped device, including the final `\0` byte.
+pub const DM_UUID_LEN: usize = 129;
+
+/// The errors of the device mapper.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DmError {
+    /// The parameters are invalid.
+    InvalidArgs(&'static str),
+    /// The mapped device or the underlying device does not exist.
+    NotFound,
+    /// A mapped device with the same name or UUID already exists.
+    Exists,
+    /// The mapped device is in use.
+    Busy,
+    /// The key is not found.
+    NoKey,
+}
+
+static DEVICES: Mutex<BTreeMap<String, Arc<MappedDevice>>> = Mutex::new(BTreeMap::new());
+
+/// Creates a mapped device without tables, and registers it in `aster-block`.
+///
+/// The `uuid` may be empty.
+pub fn create_device(name: &str, uuid: &str) -> Result<Arc<MappedDevice>, DmError> {
+    if name.is_empty() || name.len() >= DM_NAME_LEN || name.contains('/') {
+        return Err(DmError::InvalidArgs("invalid device name"));
+    }
+    if uuid.len() >= DM_UUID_LEN {
+        return Err(DmError::InvalidArgs("invalid device uuid"));
+    }
+
+    let mut devices = DEVICES.lock();
+    if devices.contains_key(name)
+        || (!uuid.is_empty() && devices.values().any(|device| device.uuid() == uuid))
+    {
+        return Err(DmError::Exists);
+    }
+
+    let minor = (0..)
+        .find(|minor| devices.values().all(|device| device.minor() != *minor))
+        .unwrap();
+    let device = Arc::new(MappedDevice::new(name.into(), uuid.into(), minor));
+    aster_block::register_device(device.block_device_name(), device.clone());
+    devices.insert(name.into(), device.clone());
+    Ok(device)
+}
+
+/// Removes the mapped device named `name`, and unregisters it from `aster-block`.
+///
+/// The device cannot be removed if it is still used elsewhere, e.g., by a file system or
+/// by the table of another mapped device.
+pub fn remove_device(name: &str) -> Result<Arc<MappedDevice>, DmError> {
+    let mut devices = DEVICES.lock();
+    let Some(device) = devices.get(name) else {
+        return Err(DmError::NotFound);
+    };
+    // One reference is held by `DEVICES` and another by `aster-block`.
+    if Arc::strong_count(device) > 2 {
+        return Err(DmError::Busy);
+    }
+
+    let device = devices.remove(name).unwrap();
+    aster_block::unregister_device(&device.block_device_name());
+    Ok(device)
+}
+
+/// Gets the mapped device named `name`.
+pub fn get_device(name: &str) -> Option<Arc<MappedDevice>> {
+    DEVICES.lock().get(name).cloned()
+}
+
+/// Gets the mapped device whose UUID is `uuid`.
+pub fn get_device_by_uuid(uuid: &str) -> Option<Arc<MappedDevice>> {
+    if uuid.is_empty() {
+        return None;
+    }
+    DEVICES
+        .lock()
+        .values()
+        .find(|device| device.uuid() == uuid)
+        .cloned()
+}
+
+/// Returns all the mapped devices, sorted by their names.
+pub fn all_devices() -> Vec<Arc<MappedDevice>> {
+    DEVICES.lock().values().cloned().collect()
+}
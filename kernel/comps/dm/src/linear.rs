@@ -0,0 +1,65 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The `linear` target.
+//!
+//! The parameters are `<device> <offset>`, which map the target to the sectors of
+//! `<device>` starting from `<offset>`.
+
+use alloc::{format, string::String, sync::Arc};
+
+use aster_block::{bio::SubmittedBio, BlockDevice};
+
+use crate::{
+    table::{check_device_range, lookup_device, Target},
+    DmError,
+};
+
+#[derive(Debug)]
+pub(crate) struct LinearTarget {
+    device_path: String,
+    device: Arc<dyn BlockDevice>,
+    offset: u64,
+}
+
+impl LinearTarget {
+    pub(crate) fn new(params: &str, len: u64) -> Result<Self, DmError> {
+        let mut args = params.split_ascii_whitespace();
+        let (Some(device_path), Some(offset), None) = (args.next(), args.next(), args.next())
+        else {
+            return Err(DmError::InvalidArgs("invalid linear target parameters"));
+        };
+        let offset = offset
+            .parse()
+            .map_err(|_| DmError::InvalidArgs("invalid linear target offset"))?;
+
+        let device = lookup_device(device_path)?;
+        check_device_range(&device, offset, len)?;
+
+        Ok(Self {
+            device_path: device_path.into(),
+            device,
+            offset,
+        })
+    }
+}
+
+impl Target for LinearTarget {
+    fn type_name(&self) -> &'static str {
+        "linear"
+    }
+
+    fn map(&self, bio: SubmittedBio, target_start: u64) {
+        let start_sid = bio.sid_range().start - target_start + self.offset;
+        let segments = bio.segments().to_vec();
+        bio.redirect(start_sid, segments, None)
+            .forward(self.device.as_ref());
+    }
+
+    fn device(&self) -> &Arc<dyn BlockDevice> {
+        &self.device
+    }
+
+    fn params(&self) -> String {
+        format!("{} {}", self.device_path, self.offset)
+    }
+}
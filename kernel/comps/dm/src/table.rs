@@ -0,0 +1,236 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::{boxed::Box, string::String, sync::Arc, vec::Vec};
+use core::fmt::Debug;
+
+use aster_block::{
+    bio::{BioEnqueueError, BioSegment, BioType, SubmittedBio},
+    id::Sid,
+    BlockDevice, SECTOR_SIZE,
+};
+
+use crate::{crypt::CryptTarget, linear::LinearTarget, DmError};
+
+/// A way of mapping a contiguous range of sectors of a mapped device.
+pub trait Target: Send + Sync + Debug {
+    /// Returns the type name, e.g., `linear`.
+    fn type_name(&self) -> &'static str;
+
+    /// Maps `bio` to the underlying device, which also takes the responsibility of
+    /// completing it.
+    ///
+    /// The `bio` is within the target, which starts at the `target_start` sector of the
+    /// mapped device. Flush requests are not passed to this method.
+    fn map(&self, bio: SubmittedBio, target_start: u64);
+
+    /// Returns the underlying device.
+    fn device(&self) -> &Arc<dyn BlockDevice>;
+
+    /// Returns the parameters, in the same format as those in the table.
+    fn params(&self) -> String;
+}
+
+/// An entry of a table, as it is specified by the user.
+#[derive(Clone, Copy, Debug)]
+pub struct TargetSpec<'a> {
+    /// The first sector of the target in the mapped device.
+    pub start: u64,
+    /// The number of sectors of the target.
+    pub len: u64,
+    /// The type name of the target.
+    pub type_name: &'a str,
+    /// The parameters of the target.
+    pub params: &'a str,
+}
+
+/// A table that maps the sectors of a mapped device.
+#[derive(Debug)]
+pub struct Table {
+    entries: Vec<Entry>,
+    nr_sectors: u64,
+}
+
+#[derive(Debug)]
+struct Entry {
+    start: u64,
+    len: u64,
+    target: Box<dyn Target>,
+}
+
+impl Table {
+    /// Constructs a table from the target specifications.
+    ///
+    /// The targets must be sorted and contiguous, starting from sector zero.
+    pub fn new(specs: &[TargetSpec]) -> Result<Self, DmError> {
+        if specs.is_empty() {
+            return Err(DmError::InvalidArgs("the table is empty"));
+        }
+
+        let mut entries = Vec::with_capacity(specs.len());
+        let mut nr_sectors = 0;
+        for spec in specs {
+            if spec.start != nr_sectors {
+                return Err(DmError::InvalidArgs("the targets are not contiguous"));
+            }
+            if spec.len == 0 {
+                return Err(DmError::InvalidArgs("the target is empty"));
+            }
+            let params = spec.params.trim();
+            let target: Box<dyn Target> = match spec.type_name {
+                "linear" => Box::new(LinearTarget::new(params, spec.len)?),
+                "crypt" => Box::new(CryptTarget::new(params, spec.len)?),
+                _ => return Err(DmError::InvalidArgs("unknown target type")),
+            };
+            entries.push(Entry {
+                start: spec.start,
+                len: spec.len,
+                target,
+            });
+            nr_sectors = spec
+                .start
+                .checked_add(spec.len)
+                .ok_or(DmError::InvalidArgs("the target is too large"))?;
+        }
+
+        Ok(Self {
+            entries,
+            nr_sectors,
+        })
+    }
+
+    /// Returns the number of sectors.
+    pub fn nr_sectors(&self) -> u64 {
+        self.nr_sectors
+    }
+
+    /// Returns the targets with their first sectors and their numbers of sectors.
+    pub fn targets(&self) -> impl Iterator<Item = (u64, u64, &dyn Target)> {
+        self.entries
+            .iter()
+            .map(|entry| (entry.start, entry.len, entry.target.as_ref()))
+    }
+
+    /// Returns the upper limit for the number of segments per bio.
+    pub(crate) fn max_nr_segments_per_bio(&self) -> usize {
+        self.entries
+            .iter()
+            .map(|entry| entry.target.device().metadata().max_nr_segments_per_bio)
+            .min()
+            .unwrap()
+    }
+
+    /// Maps `bio` to the targets.
+    pub(crate) fn map(&self, bio: SubmittedBio) -> Result<(), BioEnqueueError> {
+        if bio.type_() == BioType::Flush {
+            self.map_flush(bio);
+            return Ok(());
+        }
+
+        let start = bio.sid_range().start.to_raw();
+        let end = bio.sid_range().end.to_raw();
+        if end > self.nr_sectors || start >= end {
+            return Err(BioEnqueueError::Refused);
+        }
+
+        let first = self
+            .entries
+            .partition_point(|entry| entry.start + entry.len <= start);
+        let last = self.entries.partition_point(|entry| entry.start < end) - 1;
+        if first == last {
+            let entry = &self.entries[first];
+            entry.target.map(bio, entry.start);
+            return Ok(());
+        }
+
+        let boundaries: Vec<u64> = self.entries[first + 1..=last]
+            .iter()
+            .map(|entry| entry.start)
+            .collect();
+        let pieces = divide_segments(&bio, &boundaries);
+        for (entry, piece) in self.entries[first..=last].iter().zip(bio.fork(pieces)) {
+            entry.target.map(piece, entry.start);
+        }
+        Ok(())
+    }
+
+    /// Forwards the flush request to each of the underlying devices.
+    fn map_flush(&self, bio: SubmittedBio) {
+        let mut devices: Vec<&Arc<dyn BlockDevice>> = Vec::new();
+        for entry in self.entries.iter() {
+            let device = entry.target.device();
+            if !devices.iter().any(|other| Arc::ptr_eq(other, device)) {
+                devices.push(device);
+            }
+        }
+
+        let children = devices.iter().map(|_| (Sid::new(0), Vec::new())).collect();
+        for (device, child) in devices.into_iter().zip(bio.fork(children)) {
+            child.forward(device.as_ref());
+        }
+    }
+}
+
+/// Divides the memory segments of `bio` at the sectors in `boundaries`.
+///
+/// The `boundaries` must be sorted and strictly within the sectors of `bio`. Returns the
+/// first sector and the memory segments of each part.
+fn divide_segments(bio: &SubmittedBio, boundaries: &[u64]) -> Vec<(Sid, Vec<BioSegment>)> {
+    let mut parts = Vec::with_capacity(boundaries.len() + 1);
+    let mut boundaries = boundaries.iter().copied().peekable();
+    let mut part_start = bio.sid_range().start.to_raw();
+    let mut part_segments = Vec::new();
+    let mut sector = part_start;
+
+    for segment in bio.segments() {
+        let nbytes = segment.nbytes();
+        let mut offset = 0;
+        while offset < nbytes {
+            let remain = ((nbytes - offset) / SECTOR_SIZE) as u64;
+            let nsectors = match boundaries.peek() {
+                Some(&boundary) if boundary < sector + remain => boundary - sector,
+                _ => remain,
+            };
+            let len = nsectors as usize * SECTOR_SIZE;
+            if offset == 0 && len == nbytes {
+                part_segments.push(segment.clone());
+            } else {
+                part_segments.push(segment.slice(offset, len));
+            }
+            offset += len;
+            sector += nsectors;
+
+            if boundaries.peek() == Some(&sector) {
+                boundaries.next();
+                parts.push((Sid::new(part_start), core::mem::take(&mut part_segments)));
+                part_start = sector;
+            }
+        }
+    }
+    parts.push((Sid::new(part_start), part_segments));
+
+    parts
+}
+
+/// Looks up the underlying device by its path.
+pub(crate) fn lookup_device(path: &str) -> Result<Arc<dyn BlockDevice>, DmError> {
+    if let Some(name) = path.strip_prefix("/dev/mapper/") {
+        return crate::get_device(name)
+            .map(|device| device as Arc<dyn BlockDevice>)
+            .ok_or(DmError::NotFound);
+    }
+    let name = path.strip_prefix("/dev/").unwrap_or(path);
+    aster_block::get_device(name).ok_or(DmError::NotFound)
+}
+
+/// Checks that the `len` sectors starting from `start` are within `device`.
+pub(crate) fn check_device_range(
+    device: &Arc<dyn BlockDevice>,
+    start: u64,
+    len: u64,
+) -> Result<(), DmError> {
+    let nr_sectors = device.metadata().nr_sectors as u64;
+    match start.checked_add(len) {
+        Some(end) if end <= nr_sectors => Ok(()),
+        _ => Err(DmError::InvalidArgs("the target exceeds the device")),
+    }
+}
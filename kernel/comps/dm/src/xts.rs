@@ -0,0 +1,132 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The XTS mode of AES (IEEE 1619).
+//!
+//! Only data units whose lengths are multiples of the AES block size are supported,
+//! so ciphertext stealing is never needed for sectors.
+
+use alloc::boxed::Box;
+
+use aster_crypto::api::{self, BlockCipherKey};
+
+const BLOCK_LEN: usize = 16;
+
+/// An AES-XTS cipher.
+///
+/// The AES implementation is looked up from the crypto API, so it is accelerated by the
+/// hardware if possible.
+pub(crate) struct Xts {
+    data_key: Box<dyn BlockCipherKey>,
+    tweak_key: Box<dyn BlockCipherKey>,
+}
+
+impl Xts {
+    /// Creates a cipher from the key, which consists of the data key and the tweak key.
+    ///
+    /// The key must be 32 bytes (AES-128-XTS) or 64 bytes (AES-256-XTS) long.
+    pub(crate) fn new(key: &[u8]) -> Option<Self> {
+        if key.len() != 32 && key.len() != 64 {
+            return None;
+        }
+        let (data_key, tweak_key) = key.split_at(key.len() / 2);
+        let aes = api::find_block_cipher("aes")?;
+        Some(Self {
+            data_key: aes.new_key(data_key)?,
+            tweak_key: aes.new_key(tweak_key)?,
+        })
+    }
+
+    /// Encrypts the data unit in place with the initial vector `iv`.
+    pub(crate) fn encrypt(&self, iv: &[u8; BLOCK_LEN], data: &mut [u8]) {
+        self.process(iv, data, |block| self.data_key.encrypt_block(block));
+    }
+
+    /// Decrypts the data unit in place with the initial vector `iv`.
+    pub(crate) fn decrypt(&self, iv: &[u8; BLOCK_LEN], data: &mut [u8]) {
+        self.process(iv, data, |block| self.data_key.decrypt_block(block));
+    }
+
+    fn process(&self, iv: &[u8; BLOCK_LEN], data: &mut [u8], f: impl Fn(&mut [u8])) {
+        debug_assert_eq!(data.len() % BLOCK_LEN, 0);
+
+        let mut tweak = *iv;
+        self.tweak_key.encrypt_block(&mut tweak);
+        for block in data.chunks_exact_mut(BLOCK_LEN) {
+            xor(block, &tweak);
+            f(block);
+            xor(block, &tweak);
+            multiply_by_alpha(&mut tweak);
+        }
+    }
+}
+
+impl core::fmt::Debug for Xts {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        // Never show the keys.
+        f.debug_struct("Xts").finish_non_exhaustive()
+    }
+}
+
+fn xor(block: &mut [u8], tweak: &[u8; BLOCK_LEN]) {
+    for (byte, tweak_byte) in block.iter_mut().zip(tweak) {
+        *byte ^= tweak_byte;
+    }
+}
+
+/// Multiplies the tweak by the primitive element of GF(2^128), in little endian.
+fn multiply_by_alpha(tweak: &mut [u8; BLOCK_LEN]) {
+    let mut carry = 0;
+    for byte in tweak.iter_mut() {
+        let next_carry = *byte >> 7;
+        *byte = (*byte << 1) | carry;
+        carry = next_carry;
+    }
+    if carry != 0 {
+        tweak[0] ^= 0x87;
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn ieee1619_vector_1() {
+        let xts = Xts::new(&[0u8; 32]).unwrap();
+        let mut data = [0u8; 32];
+        xts.encrypt(&[0u8; BLOCK_LEN], &mut data);
+        assert_eq!(
+            data,
+            [
+                0x91, 0x7c, 0xf6, 0x9e, 0xbd, 0x68, 0xb2, 0xec, 0x9b, 0x9f, 0xe9, 0xa3, 0xea, 0xdd,
+                0xa6, 0x92, 0xcd, 0x43, 0xd2, 0xf5, 0x95, 0x98, 0xed, 0x85, 0x8c, 0x02, 0xc2, 0x65,
+                0x2f, 0xbf, 0x92, 0x2e,
+            ]
+        );
+        xts.decrypt(&[0u8; BLOCK_LEN], &mut data);
+        assert_eq!(data, [0u8; 32]);
+    }
+
+    #[ktest]
+    fn round_trip() {
+        let key: [u8; 64] = core::array::from_fn(|i| i as u8);
+        let xts = Xts::new(&key).unwrap();
+        let mut iv = [0u8; BLOCK_LEN];
+        iv[0] = 42;
+
+        let plain: [u8; 512] = core::array::from_fn(|i| (i * 7) as u8);
+        let mut data = plain;
+        xts.encrypt(&iv, &mut data);
+        assert_ne!(data, plain);
+        xts.decrypt(&iv, &mut data);
+        assert_eq!(data, plain);
+    }
+
+    #[ktest]
+    fn invalid_key_len() {
+        assert!(Xts::new(&[0u8; 16]).is_none());
+        assert!(Xts::new(&[0u8; 48]).is_none());
+    }
+}
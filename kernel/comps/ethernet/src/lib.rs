@@ -0,0 +1,203 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The Ethernet subsystem of Asterinas.
+//!
+//! This crate drives the Ethernet controllers of real boards, which consist of a MAC in the
+//! SoC and an external PHY. It is organized in three layers:
+//!
+//! - The MDIO layer (`mdio`) abstracts the management buses of the PHYs. Each bus implements
+//!   the [`MdioBus`] trait, which reads and writes the Clause 22 registers of the PHYs.
+//! - The PHY layer (`phy`) identifies the PHYs, runs the autonegotiation, and tracks the
+//!   state of the links with a state machine. The PHYs that report the link changes with
+//!   interrupts use the vendor drivers, and the other PHYs are polled periodically.
+//! - The MAC layer implements the [`EthernetMac`] trait on top of
+//!   [`aster_network::AnyNetworkDevice`]. Currently, the Synopsys DesignWare Ethernet
+//!   QoS controllers (`stmmac`, version 4.x and 5.x), which are used by the StarFive JH7110
+//!   on VisionFive 2, are supported.
+//!
+//! Each controller is registered as a network device named `ethN` in `aster-network`.
+//! The controllers are discovered through the device tree. Their descriptor rings and packet
+//! buffers are synchronized with the CPU caches by the `DmaStream` API on non-coherent
+//! platforms.
+//!
+//! There is no interrupt controller support on RISC-V yet, so the controllers and the PHYs
+//! are polled with the system timer.
+#![no_std]
+#![deny(unsafe_code)]
+
+extern crate alloc;
+
+mod mdio;
+mod phy;
+#[cfg(target_arch = "riscv64")]
+mod probe;
+#[cfg(target_arch = "riscv64")]
+mod stmmac;
+
+use alloc::{format, string::String, sync::Arc, vec::Vec};
+use core::{
+    hint::spin_loop,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use aster_network::AnyNetworkDevice;
+use component::{init_component, ComponentInitError};
+use log::info;
+use ostd::{
+    sync::{LocalIrqDisabled, SpinLock},
+    timer::{self, Jiffies},
+};
+
+pub use self::{
+    mdio::{find_phy, read_phy_id, MdioBus, MdioError, NR_PHY_ADDRS},
+    phy::{Duplex, Link, LinkEvent, Phy, PhyState, Speed},
+};
+
+#[init_component]
+fn ethernet_component_init() -> Result<(), ComponentInitError> {
+    #[cfg(target_arch = "riscv64")]
+    probe::probe_ports();
+
+    timer::register_callback(poll_ports);
+    Ok(())
+}
+
+/// An Ethernet MAC.
+///
+/// The methods are called with the device locked, possibly in the interrupt context, so they
+/// must not sleep.
+pub trait EthernetMac: AnyNetworkDevice {
+    /// Programs the MAC with the speed and the duplex of the link, or stops the transfers if
+    /// the link is down.
+    fn adjust_link(&mut self, link: Option<Link>);
+
+    /// Returns whether any transmitted packets are waiting to be reclaimed.
+    fn has_completed_tx(&self) -> bool;
+}
+
+/// Registers a MAC as a network device, with the PHY that manages its link.
+///
+/// If there is no PHY, the link is fixed (e.g., to a switch), and `fixed_link` describes it.
+/// Returns the name of the network device.
+pub fn register_port<M: EthernetMac>(mac: M, phy: Option<Phy>, fixed_link: Option<Link>) -> String {
+    let name = format!("eth{}", NEXT_INDEX.fetch_add(1, Ordering::Relaxed));
+    let device = Arc::new(SpinLock::new(mac));
+
+    match &phy {
+        Some(phy) => {
+            info!(
+                "[Ethernet]: {}: PHY {:08x} ({}) at address {}",
+                name,
+                phy.id(),
+                phy.driver_name(),
+                phy.addr()
+            );
+            phy.start(now_ms());
+        }
+        None => {
+            info!("[Ethernet]: {}: fixed link {:?}", name, fixed_link);
+            device.lock().adjust_link(fixed_link);
+        }
+    }
+
+    aster_network::register_device(name.clone(), device.clone());
+    PORTS.lock().push(Arc::new(Port {
+        name: name.clone(),
+        device,
+        phy,
+    }));
+    name
+}
+
+/// Returns the names of all the registered network devices.
+pub fn all_ports() -> Vec<String> {
+    PORTS
+        .lock()
+        .iter()
+        .map(|port| String::from(port.name()))
+        .collect()
+}
+
+/// An Ethernet port, i.e., a MAC and the PHY that manages its link.
+struct Port<M: EthernetMac> {
+    name: String,
+    device: Arc<SpinLock<M, LocalIrqDisabled>>,
+    phy: Option<Phy>,
+}
+
+/// The type-erased operations of [`Port`]s.
+trait PollPort: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Polls the PHY and the MAC, and notifies the network stack of the events.
+    fn poll(&self, now_ms: u64);
+}
+
+impl<M: EthernetMac> PollPort for Port<M> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn poll(&self, now_ms: u64) {
+        if let Some(event) = self.phy.as_ref().and_then(|phy| phy.poll(now_ms)) {
+            let link = match event {
+                LinkEvent::Up(link) => {
+                    info!(
+                        "[Ethernet]: {}: link is up, {:?} {:?} duplex",
+                        self.name, link.speed, link.duplex
+                    );
+                    Some(link)
+                }
+                LinkEvent::Down => {
+                    info!("[Ethernet]: {}: link is down", self.name);
+                    None
+                }
+            };
+            self.device.lock().adjust_link(link);
+        }
+
+        // The network stack is notified as if the MAC raised the interrupts.
+        let (can_receive, has_completed_tx) = {
+            let device = self.device.lock();
+            (device.can_receive(), device.has_completed_tx())
+        };
+        if can_receive {
+            aster_network::handle_recv_irq(&self.name);
+        }
+        if has_completed_tx {
+            aster_network::handle_send_irq(&self.name);
+        }
+    }
+}
+
+/// Polls `cond` until it returns `Some`, or until `timeout_us` microseconds elapse.
+pub(crate) fn poll_until<T>(timeout_us: u64, mut cond: impl FnMut() -> Option<T>) -> Option<T> {
+    let freq = ostd::arch::tsc_freq().max(1);
+    let deadline = ostd::arch::read_tsc() + timeout_us.saturating_mul(freq) / 1_000_000;
+    loop {
+        if let Some(value) = cond() {
+            return Some(value);
+        }
+        if ostd::arch::read_tsc() > deadline {
+            // Check once more in case that we were preempted for a long time.
+            return cond();
+        }
+        spin_loop();
+    }
+}
+
+fn now_ms() -> u64 {
+    Jiffies::elapsed().as_duration().as_millis() as u64
+}
+
+fn poll_ports() {
+    let ports = PORTS.lock().clone();
+    let now = now_ms();
+    for port in ports {
+        port.poll(now);
+    }
+}
+
+static NEXT_INDEX: AtomicUsize = AtomicUsize::new(0);
+
+static PORTS: SpinLock<Vec<Arc<dyn PollPort>>, LocalIrqDisabled> = SpinLock::new(Vec::new());
@@ -0,0 +1,51 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The MDIO buses, which connect the MACs to the management interfaces of the PHYs.
+
+use crate::phy::{MII_PHYSID1, MII_PHYSID2};
+
+/// The number of PHY addresses on an MDIO bus.
+pub const NR_PHY_ADDRS: u8 = 32;
+
+/// The errors of MDIO buses.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MdioError {
+    /// The bus does not respond in time.
+    Timeout,
+    /// There is no PHY at the address, or the PHY is not supported.
+    NoDevice,
+    /// The PHY address or the register number is out of range.
+    InvalidArgs,
+}
+
+/// An MDIO bus, which accesses the Clause 22 registers of the PHYs on it.
+///
+/// The methods may be called in the interrupt context, so they must not sleep.
+pub trait MdioBus: Send + Sync {
+    /// Returns the name of the bus.
+    fn name(&self) -> &str;
+
+    /// Reads the register `reg` of the PHY at `phy_addr`.
+    fn read(&self, phy_addr: u8, reg: u8) -> Result<u16, MdioError>;
+
+    /// Writes `value` to the register `reg` of the PHY at `phy_addr`.
+    fn write(&self, phy_addr: u8, reg: u8, value: u16) -> Result<(), MdioError>;
+}
+
+/// Reads the identifier of the PHY at `phy_addr`.
+///
+/// Returns [`MdioError::NoDevice`] if no PHY responds at the address, in which case the bus
+/// reads all ones (or all zeros with some controllers).
+pub fn read_phy_id(bus: &dyn MdioBus, phy_addr: u8) -> Result<u32, MdioError> {
+    let id =
+        ((bus.read(phy_addr, MII_PHYSID1)? as u32) << 16) | bus.read(phy_addr, MII_PHYSID2)? as u32;
+    if id == 0 || id == u32::MAX || id & 0x1fff_ffff == 0x1fff_ffff {
+        return Err(MdioError::NoDevice);
+    }
+    Ok(id)
+}
+
+/// Scans the bus for the first PHY that responds.
+pub fn find_phy(bus: &dyn MdioBus) -> Option<u8> {
+    (0..NR_PHY_ADDRS).find(|&addr| read_phy_id(bus, addr).is_ok())
+}
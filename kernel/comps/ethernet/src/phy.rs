@@ -0,0 +1,570 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The PHYs and their state machine.
+//!
+//! The PHYs are managed through the Clause 22 registers defined by IEEE 802.3, which are
+//! the same for all the PHYs. The vendor drivers only enable and acknowledge the link change
+//! interrupts, whose registers are vendor-specific.
+//!
+//! The state machine works like the one of Linux:
+//!
+//! ```text
+//!           start           link up
+//! Ready ---------> Up/NoLink ------> Running
+//!                      ^                |
+//!                      +----------------+
+//!                          link down
+//! ```
+//!
+//! A started PHY is polled every second. If the PHY reports the link changes with interrupts,
+//! its interrupt status is also checked more frequently, so that the link changes are noticed
+//! sooner. Any MDIO error moves the PHY to the error state, which stops the polling.
+
+use alloc::sync::Arc;
+
+use bitflags::bitflags;
+use log::warn;
+use ostd::sync::{LocalIrqDisabled, SpinLock};
+
+use crate::{
+    mdio::{read_phy_id, MdioBus, MdioError},
+    poll_until,
+};
+
+// The Clause 22 registers.
+pub(crate) const MII_BMCR: u8 = 0x00;
+pub(crate) const MII_BMSR: u8 = 0x01;
+pub(crate) const MII_PHYSID1: u8 = 0x02;
+pub(crate) const MII_PHYSID2: u8 = 0x03;
+pub(crate) const MII_ADVERTISE: u8 = 0x04;
+pub(crate) const MII_LPA: u8 = 0x05;
+pub(crate) const MII_CTRL1000: u8 = 0x09;
+pub(crate) const MII_STAT1000: u8 = 0x0a;
+pub(crate) const MII_ESTATUS: u8 = 0x0f;
+
+// The bits of the basic mode control register.
+const BMCR_SPEED1000: u16 = 1 << 6;
+const BMCR_FULLDPLX: u16 = 1 << 8;
+const BMCR_ANRESTART: u16 = 1 << 9;
+const BMCR_PDOWN: u16 = 1 << 11;
+const BMCR_ANENABLE: u16 = 1 << 12;
+const BMCR_SPEED100: u16 = 1 << 13;
+const BMCR_RESET: u16 = 1 << 15;
+
+// The bits of the basic mode status register.
+const BMSR_LSTATUS: u16 = 1 << 2;
+const BMSR_ANEGCAPABLE: u16 = 1 << 3;
+const BMSR_ANEGCOMPLETE: u16 = 1 << 5;
+const BMSR_ESTATEN: u16 = 1 << 8;
+const BMSR_10HALF: u16 = 1 << 11;
+const BMSR_10FULL: u16 = 1 << 12;
+const BMSR_100HALF: u16 = 1 << 13;
+const BMSR_100FULL: u16 = 1 << 14;
+
+// The bits of the advertisement register and the link partner ability register.
+const ADVERTISE_CSMA: u16 = 0x0001;
+const ADVERTISE_10HALF: u16 = 1 << 5;
+const ADVERTISE_10FULL: u16 = 1 << 6;
+const ADVERTISE_100HALF: u16 = 1 << 7;
+const ADVERTISE_100FULL: u16 = 1 << 8;
+const ADVERTISE_PAUSE_CAP: u16 = 1 << 10;
+const ADVERTISE_PAUSE_ASYM: u16 = 1 << 11;
+const ADVERTISE_ALL: u16 = ADVERTISE_10HALF
+    | ADVERTISE_10FULL
+    | ADVERTISE_100HALF
+    | ADVERTISE_100FULL
+    | ADVERTISE_PAUSE_CAP
+    | ADVERTISE_PAUSE_ASYM;
+
+// The bits of the 1000BASE-T control register. The same bits of the 1000BASE-T status
+// register are shifted left by two.
+const ADVERTISE_1000HALF: u16 = 1 << 8;
+const ADVERTISE_1000FULL: u16 = 1 << 9;
+
+// The bits of the extended status register.
+const ESTATUS_1000_THALF: u16 = 1 << 12;
+const ESTATUS_1000_TFULL: u16 = 1 << 13;
+
+/// The time (in microseconds) that a PHY takes to reset itself.
+const RESET_TIMEOUT_US: u64 = 500_000;
+/// The interval (in milliseconds) between two polls of the link status.
+const POLL_INTERVAL_MS: u64 = 1000;
+/// The interval (in milliseconds) between two checks of the interrupt status.
+const INTERRUPT_INTERVAL_MS: u64 = 100;
+
+/// The speed of a link.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Speed {
+    Mbps10,
+    Mbps100,
+    Mbps1000,
+}
+
+/// The duplex mode of a link.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Duplex {
+    Half,
+    Full,
+}
+
+/// The parameters of an established link.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Link {
+    pub speed: Speed,
+    pub duplex: Duplex,
+}
+
+/// A change of the link reported by [`Phy::poll`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LinkEvent {
+    Up(Link),
+    Down,
+}
+
+/// The states of a PHY.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PhyState {
+    /// The PHY is probed, but not started.
+    Ready,
+    /// The PHY is started, and the autonegotiation is in progress.
+    Up,
+    /// The link is down.
+    NoLink,
+    /// The link is up.
+    Running,
+    /// The PHY is stopped.
+    Halted,
+    /// The PHY does not respond.
+    Error,
+}
+
+bitflags! {
+    /// The link modes that a PHY supports.
+    struct LinkModes: u32 {
+        const HALF_10   = 1 << 0;
+        const FULL_10   = 1 << 1;
+        const HALF_100  = 1 << 2;
+        const FULL_100  = 1 << 3;
+        const HALF_1000 = 1 << 4;
+        const FULL_1000 = 1 << 5;
+        const AUTONEG   = 1 << 6;
+    }
+}
+
+/// A PHY on an MDIO bus.
+pub struct Phy {
+    bus: Arc<dyn MdioBus>,
+    addr: u8,
+    id: u32,
+    driver: &'static dyn PhyDriver,
+    supported: LinkModes,
+    inner: SpinLock<PhyInner, LocalIrqDisabled>,
+}
+
+struct PhyInner {
+    state: PhyState,
+    link: Option<Link>,
+    has_interrupts: bool,
+    next_poll_ms: u64,
+    next_interrupt_ms: u64,
+}
+
+impl Phy {
+    /// Probes the PHY at `addr`, and resets it.
+    pub fn probe(bus: Arc<dyn MdioBus>, addr: u8) -> Result<Self, MdioError> {
+        let id = read_phy_id(&*bus, addr)?;
+        let driver: &'static dyn PhyDriver = DRIVERS
+            .iter()
+            .copied()
+            .find(|driver| driver.matches(id))
+            .unwrap_or(&GenericPhy);
+
+        let bmcr = bus.read(addr, MII_BMCR)?;
+        bus.write(addr, MII_BMCR, (bmcr & !BMCR_PDOWN) | BMCR_RESET)?;
+        poll_until(RESET_TIMEOUT_US, || match bus.read(addr, MII_BMCR) {
+            Ok(bmcr) if bmcr & BMCR_RESET != 0 => None,
+            result => Some(result),
+        })
+        .ok_or(MdioError::Timeout)??;
+
+        let supported = read_abilities(&*bus, addr)?;
+        Ok(Self {
+            bus,
+            addr,
+            id,
+            driver,
+            supported,
+            inner: SpinLock::new(PhyInner {
+                state: PhyState::Ready,
+                link: None,
+                has_interrupts: false,
+                next_poll_ms: 0,
+                next_interrupt_ms: 0,
+            }),
+        })
+    }
+
+    /// Returns the address of the PHY on the bus.
+    pub fn addr(&self) -> u8 {
+        self.addr
+    }
+
+    /// Returns the identifier of the PHY, i.e., the OUI and the model and revision numbers.
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Returns the name of the driver that is bound to the PHY.
+    pub fn driver_name(&self) -> &'static str {
+        self.driver.name()
+    }
+
+    /// Returns the state of the PHY.
+    pub fn state(&self) -> PhyState {
+        self.inner.lock().state
+    }
+
+    /// Returns the current link, or `None` if the link is down.
+    pub fn link(&self) -> Option<Link> {
+        self.inner.lock().link
+    }
+
+    /// Starts the PHY, which advertises all the supported link modes and starts the
+    /// autonegotiation.
+    pub fn start(&self, now_ms: u64) {
+        let mut inner = self.inner.lock();
+        if inner.state != PhyState::Ready && inner.state != PhyState::Halted {
+            return;
+        }
+
+        let result = self
+            .driver
+            .config_init(self)
+            .and_then(|_| self.config_aneg())
+            .and_then(|_| self.driver.config_intr(self, true));
+        match result {
+            Ok(has_interrupts) => {
+                inner.state = PhyState::Up;
+                inner.has_interrupts = has_interrupts;
+                inner.next_poll_ms = now_ms;
+                inner.next_interrupt_ms = now_ms;
+            }
+            Err(err) => {
+                warn!("[PHY]: {:08x}: cannot start: {:?}", self.id, err);
+                inner.state = PhyState::Error;
+            }
+        }
+    }
+
+    /// Stops the PHY, which powers it down.
+    pub fn stop(&self) {
+        let mut inner = self.inner.lock();
+        if inner.has_interrupts {
+            let _ = self.driver.config_intr(self, false);
+        }
+        let _ = self
+            .read(MII_BMCR)
+            .and_then(|bmcr| self.write(MII_BMCR, bmcr | BMCR_PDOWN));
+        inner.state = PhyState::Halted;
+        inner.link = None;
+    }
+
+    /// Restarts the autonegotiation.
+    pub fn restart_aneg(&self) -> Result<(), MdioError> {
+        let bmcr = self.read(MII_BMCR)?;
+        self.write(MII_BMCR, bmcr | BMCR_ANENABLE | BMCR_ANRESTART)
+    }
+
+    /// Runs the state machine, and returns the change of the link if any.
+    ///
+    /// This method should be called periodically. The link status is only read if a poll
+    /// is due at `now_ms`, or if the PHY reports a link change interrupt.
+    pub fn poll(&self, now_ms: u64) -> Option<LinkEvent> {
+        let mut inner = self.inner.lock();
+        if !matches!(
+            inner.state,
+            PhyState::Up | PhyState::NoLink | PhyState::Running
+        ) {
+            return None;
+        }
+
+        let mut is_due = now_ms >= inner.next_poll_ms;
+        if inner.has_interrupts && now_ms >= inner.next_interrupt_ms {
+            inner.next_interrupt_ms = now_ms + INTERRUPT_INTERVAL_MS;
+            match self.driver.handle_interrupt(self) {
+                Ok(has_changed) => is_due |= has_changed,
+                Err(err) => return self.fail(&mut inner, err),
+            }
+        }
+        if !is_due {
+            return None;
+        }
+        inner.next_poll_ms = now_ms + POLL_INTERVAL_MS;
+
+        let link = match self.read_status() {
+            Ok(link) => link,
+            Err(err) => return self.fail(&mut inner, err),
+        };
+        match (inner.state, link) {
+            (PhyState::Up | PhyState::NoLink, Some(link)) => {
+                inner.state = PhyState::Running;
+                inner.link = Some(link);
+                Some(LinkEvent::Up(link))
+            }
+            (PhyState::Running, Some(link)) if inner.link != Some(link) => {
+                // The link is renegotiated between two polls.
+                inner.link = Some(link);
+                Some(LinkEvent::Up(link))
+            }
+            (PhyState::Running, None) => {
+                inner.state = PhyState::NoLink;
+                inner.link = None;
+                Some(LinkEvent::Down)
+            }
+            (PhyState::Up, None) => {
+                inner.state = PhyState::NoLink;
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// Moves the PHY to the error state after an MDIO error.
+    fn fail(&self, inner: &mut PhyInner, err: MdioError) -> Option<LinkEvent> {
+        warn!("[PHY]: {:08x}: MDIO error: {:?}", self.id, err);
+        let was_up = inner.link.is_some();
+        inner.state = PhyState::Error;
+        inner.link = None;
+        was_up.then_some(LinkEvent::Down)
+    }
+
+    /// Writes the advertisement registers and restarts the autonegotiation.
+    fn config_aneg(&self) -> Result<(), MdioError> {
+        if !self.supported.contains(LinkModes::AUTONEG) {
+            // Force the fastest supported mode.
+            let bmcr = if self.supported.contains(LinkModes::FULL_100) {
+                BMCR_SPEED100 | BMCR_FULLDPLX
+            } else if self.supported.contains(LinkModes::HALF_100) {
+                BMCR_SPEED100
+            } else if self.supported.contains(LinkModes::FULL_10) {
+                BMCR_FULLDPLX
+            } else {
+                0
+            };
+            return self.write(MII_BMCR, bmcr);
+        }
+
+        let mut advertise = ADVERTISE_CSMA | ADVERTISE_PAUSE_CAP | ADVERTISE_PAUSE_ASYM;
+        for (mode, bit) in [
+            (LinkModes::HALF_10, ADVERTISE_10HALF),
+            (LinkModes::FULL_10, ADVERTISE_10FULL),
+            (LinkModes::HALF_100, ADVERTISE_100HALF),
+            (LinkModes::FULL_100, ADVERTISE_100FULL),
+        ] {
+            if self.supported.contains(mode) {
+                advertise |= bit;
+            }
+        }
+        let old_advertise = self.read(MII_ADVERTISE)?;
+        self.write(
+            MII_ADVERTISE,
+            (old_advertise & !(ADVERTISE_ALL | 0x1f)) | advertise,
+        )?;
+
+        if self.supports_gigabit() {
+            let mut ctrl1000 = self.read(MII_CTRL1000)?;
+            ctrl1000 &= !(ADVERTISE_1000HALF | ADVERTISE_1000FULL);
+            if self.supported.contains(LinkModes::HALF_1000) {
+                ctrl1000 |= ADVERTISE_1000HALF;
+            }
+            if self.supported.contains(LinkModes::FULL_1000) {
+                ctrl1000 |= ADVERTISE_1000FULL;
+            }
+            self.write(MII_CTRL1000, ctrl1000)?;
+        }
+
+        self.restart_aneg()
+    }
+
+    /// Reads the link status, and resolves the speed and the duplex if the link is up.
+    fn read_status(&self) -> Result<Option<Link>, MdioError> {
+        // The link status bit is latched low, so the first read returns whether the link
+        // has been down since the last read.
+        self.read(MII_BMSR)?;
+        let bmsr = self.read(MII_BMSR)?;
+        if bmsr & BMSR_LSTATUS == 0 {
+            return Ok(None);
+        }
+
+        let bmcr = self.read(MII_BMCR)?;
+        if bmcr & BMCR_ANENABLE == 0 {
+            let speed = if bmcr & BMCR_SPEED1000 != 0 {
+                Speed::Mbps1000
+            } else if bmcr & BMCR_SPEED100 != 0 {
+                Speed::Mbps100
+            } else {
+                Speed::Mbps10
+            };
+            let duplex = if bmcr & BMCR_FULLDPLX != 0 {
+                Duplex::Full
+            } else {
+                Duplex::Half
+            };
+            return Ok(Some(Link { speed, duplex }));
+        }
+        if bmsr & BMSR_ANEGCOMPLETE == 0 {
+            return Ok(None);
+        }
+
+        if self.supports_gigabit() {
+            let common = self.read(MII_CTRL1000)? & (self.read(MII_STAT1000)? >> 2);
+            if common & ADVERTISE_1000FULL != 0 {
+                return Ok(Some(Link {
+                    speed: Speed::Mbps1000,
+                    duplex: Duplex::Full,
+                }));
+            }
+            if common & ADVERTISE_1000HALF != 0 {
+                return Ok(Some(Link {
+                    speed: Speed::Mbps1000,
+                    duplex: Duplex::Half,
+                }));
+            }
+        }
+
+        let common = self.read(MII_ADVERTISE)? & self.read(MII_LPA)?;
+        let (speed, duplex) = if common & ADVERTISE_100FULL != 0 {
+            (Speed::Mbps100, Duplex::Full)
+        } else if common & ADVERTISE_100HALF != 0 {
+            (Speed::Mbps100, Duplex::Half)
+        } else if common & ADVERTISE_10FULL != 0 {
+            (Speed::Mbps10, Duplex::Full)
+        } else {
+            (Speed::Mbps10, Duplex::Half)
+        };
+        Ok(Some(Link { speed, duplex }))
+    }
+
+    fn supports_gigabit(&self) -> bool {
+        self.supported
+            .intersects(LinkModes::HALF_1000 | LinkModes::FULL_1000)
+    }
+
+    /// Reads a register of the PHY.
+    pub fn read(&self, reg: u8) -> Result<u16, MdioError> {
+        self.bus.read(self.addr, reg)
+    }
+
+    /// Writes a register of the PHY.
+    pub fn write(&self, reg: u8, value: u16) -> Result<(), MdioError> {
+        self.bus.write(self.addr, reg, value)
+    }
+}
+
+/// Reads the link modes that are supported by the PHY.
+fn read_abilities(bus: &dyn MdioBus, addr: u8) -> Result<LinkModes, MdioError> {
+    let bmsr = bus.read(addr, MII_BMSR)?;
+    let mut supported = LinkModes::empty();
+    for (bit, mode) in [
+        (BMSR_ANEGCAPABLE, LinkModes::AUTONEG),
+        (BMSR_10HALF, LinkModes::HALF_10),
+        (BMSR_10FULL, LinkModes::FULL_10),
+        (BMSR_100HALF, LinkModes::HALF_100),
+        (BMSR_100FULL, LinkModes::FULL_100),
+    ] {
+        if bmsr & bit != 0 {
+            supported |= mode;
+        }
+    }
+
+    if bmsr & BMSR_ESTATEN != 0 {
+        let estatus = bus.read(addr, MII_ESTATUS)?;
+        if estatus & ESTATUS_1000_THALF != 0 {
+            supported |= LinkModes::HALF_1000;
+        }
+        if estatus & ESTATUS_1000_TFULL != 0 {
+            supported |= LinkModes::FULL_1000;
+        }
+    }
+    Ok(supported)
+}
+
+/// A PHY driver, which handles the vendor-specific registers of the PHYs.
+trait PhyDriver: Sync {
+    fn name(&self) -> &'static str;
+
+    /// Returns whether the driver supports the PHY with the identifier.
+    fn matches(&self, id: u32) -> bool;
+
+    /// Configures the PHY before the autonegotiation starts.
+    fn config_init(&self, _phy: &Phy) -> Result<(), MdioError> {
+        Ok(())
+    }
+
+    /// Enables or disables the link change interrupts.
+    ///
+    /// Returns whether the interrupts are supported.
+    fn config_intr(&self, _phy: &Phy, _enable: bool) -> Result<bool, MdioError> {
+        Ok(false)
+    }
+
+    /// Acknowledges the interrupts, and returns whether the link may have changed.
+    fn handle_interrupt(&self, _phy: &Phy) -> Result<bool, MdioError> {
+        Ok(false)
+    }
+}
+
+/// The drivers of the PHYs with vendor-specific features, in addition to [`GenericPhy`].
+static DRIVERS: &[&dyn PhyDriver] = &[&Yt8531];
+
+/// The driver of the PHYs that only support the standard registers.
+struct GenericPhy;
+
+impl PhyDriver for GenericPhy {
+    fn name(&self) -> &'static str {
+        "Generic PHY"
+    }
+
+    fn matches(&self, _id: u32) -> bool {
+        true
+    }
+}
+
+/// The driver of the Motorcomm YT8531 gigabit PHYs, which are used by VisionFive 2.
+struct Yt8531;
+
+impl Yt8531 {
+    const PHY_ID: u32 = 0x4f51_e91b;
+    const INTERRUPT_ENABLE: u8 = 0x12;
+    const INTERRUPT_STATUS: u8 = 0x13;
+    const INTERRUPT_LINK_UP: u16 = 1 << 10;
+    const INTERRUPT_LINK_DOWN: u16 = 1 << 11;
+}
+
+impl PhyDriver for Yt8531 {
+    fn name(&self) -> &'static str {
+        "YT8531 Gigabit Ethernet"
+    }
+
+    fn matches(&self, id: u32) -> bool {
+        id == Self::PHY_ID
+    }
+
+    fn config_intr(&self, phy: &Phy, enable: bool) -> Result<bool, MdioError> {
+        // Clear the pending interrupts before enabling them.
+        phy.read(Self::INTERRUPT_STATUS)?;
+        let mask = if enable {
+            Self::INTERRUPT_LINK_UP | Self::INTERRUPT_LINK_DOWN
+        } else {
+            0
+        };
+        phy.write(Self::INTERRUPT_ENABLE, mask)?;
+        Ok(true)
+    }
+
+    fn handle_interrupt(&self, phy: &Phy) -> Result<bool, MdioError> {
+        // Reading the status register clears the interrupts.
+        let status = phy.read(Self::INTERRUPT_STATUS)?;
+        Ok(status & (Self::INTERRUPT_LINK_UP | Self::INTERRUPT_LINK_DOWN) != 0)
+    }
+}
@@ -0,0 +1,147 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Discovery of Ethernet controllers and their PHYs in the device tree.
+
+use alloc::{string::ToString, sync::Arc};
+
+use aster_network::{AnyNetworkDevice, EthernetAddr};
+use fdt::node::FdtNode;
+use log::{info, warn};
+use ostd::{arch::boot::DEVICE_TREE, io::MmioRegion};
+
+use crate::{
+    mdio::{find_phy, MdioBus},
+    phy::{Duplex, Link, Phy, Speed},
+    register_port,
+    stmmac::{Stmmac, StmmacMdio},
+};
+
+/// The compatible strings of the DWMAC 4.x and 5.x controllers.
+const STMMAC_COMPATIBLES: &[&str] = &[
+    "snps,dwmac-4.00",
+    "snps,dwmac-4.10a",
+    "snps,dwmac-4.20a",
+    "snps,dwmac-5.10a",
+    "snps,dwmac-5.20",
+    "starfive,jh7110-dwmac",
+];
+
+/// Probes the Ethernet controllers described in the device tree, and registers them.
+pub(crate) fn probe_ports() {
+    for node in DEVICE_TREE.get().unwrap().all_nodes() {
+        let Some(compatible) = node.compatible() else {
+            continue;
+        };
+        if !compatible.all().any(|c| STMMAC_COMPATIBLES.contains(&c)) {
+            continue;
+        }
+        if node
+            .property("status")
+            .and_then(|prop| prop.as_str())
+            .is_some_and(|status| status != "okay" && status != "ok")
+        {
+            continue;
+        }
+
+        let io_mem = match MmioRegion::claim_fdt_reg(&node) {
+            Ok(io_mem) => Arc::new(io_mem),
+            Err(err) => {
+                warn!(
+                    "[Ethernet]: {}: cannot claim the registers: {:?}",
+                    node.name, err
+                );
+                continue;
+            }
+        };
+
+        let is_cache_coherent = node.property("dma-coherent").is_some();
+        let mac = match Stmmac::new(
+            node.name.to_string(),
+            io_mem.clone(),
+            read_mac_addr(&node),
+            is_cache_coherent,
+        ) {
+            Ok(mac) => mac,
+            Err(err) => {
+                warn!(
+                    "[Ethernet]: {}: initialization failed: {:?}",
+                    node.name, err
+                );
+                continue;
+            }
+        };
+        info!(
+            "[Ethernet]: found stmmac controller {}, MAC address {:02x?}",
+            mac.name(),
+            mac.mac_addr().0
+        );
+
+        let fixed_link = read_fixed_link(&node);
+        let phy = if fixed_link.is_some() {
+            None
+        } else {
+            let bus: Arc<dyn MdioBus> = Arc::new(StmmacMdio::new(node.name.to_string(), io_mem));
+            match probe_phy(&node, bus) {
+                Some(phy) => Some(phy),
+                None => {
+                    warn!("[Ethernet]: {}: no PHY is found", node.name);
+                    continue;
+                }
+            }
+        };
+        register_port(mac, phy, fixed_link);
+    }
+}
+
+/// Reads the MAC address assigned by the firmware.
+fn read_mac_addr(node: &FdtNode) -> Option<EthernetAddr> {
+    ["local-mac-address", "mac-address"]
+        .iter()
+        .filter_map(|name| node.property(name))
+        .filter_map(|prop| <[u8; 6]>::try_from(prop.value).ok())
+        .find(|addr| addr.iter().any(|byte| *byte != 0) && addr[0] & 1 == 0)
+        .map(EthernetAddr)
+}
+
+/// Reads the `fixed-link` subnode, which describes a link without a PHY.
+fn read_fixed_link(node: &FdtNode) -> Option<Link> {
+    let fixed_link = node.children().find(|child| child.name == "fixed-link")?;
+    let speed = match fixed_link
+        .property("speed")
+        .and_then(|prop| prop.as_usize())
+    {
+        Some(1000) => Speed::Mbps1000,
+        Some(100) => Speed::Mbps100,
+        _ => Speed::Mbps10,
+    };
+    let duplex = if fixed_link.property("full-duplex").is_some() {
+        Duplex::Full
+    } else {
+        Duplex::Half
+    };
+    Some(Link { speed, duplex })
+}
+
+/// Probes the PHY referred to by `phy-handle`, or the first PHY on the bus if the property is
+/// absent.
+fn probe_phy(node: &FdtNode, bus: Arc<dyn MdioBus>) -> Option<Phy> {
+    let phy_addr = node
+        .property("phy-handle")
+        .and_then(|prop| prop.as_usize())
+        .and_then(|phandle| DEVICE_TREE.get().unwrap().find_phandle(phandle as u32))
+        .and_then(|phy_node| phy_node.property("reg"))
+        .and_then(|prop| prop.as_usize())
+        .map(|addr| addr as u8)
+        .or_else(|| find_phy(&*bus))?;
+
+    match Phy::probe(bus, phy_addr) {
+        Ok(phy) => Some(phy),
+        Err(err) => {
+            warn!(
+                "[Ethernet]: {}: cannot probe the PHY at address {}: {:?}",
+                node.name, phy_addr, err
+            );
+            None
+        }
+    }
+}
@@ -0,0 +1,133 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The DMA descriptor rings of stmmac controllers.
+//!
+//! On non-coherent platforms, a descriptor may only share a cache line with other descriptors
+//! if their ownership always changes together, since writing the line back could overwrite the
+//! status written by the controller into a neighbour descriptor. So each descriptor occupies a
+//! whole cache line on these platforms, and the controller is told to skip the padding.
+
+use ostd::{
+    mm::{Daddr, DmaDirection, DmaStream, FrameAllocOptions, HasDaddr, VmIo, PAGE_SIZE},
+    Pod,
+};
+
+/// The size of a normal descriptor, in bytes.
+pub(super) const DESC_SIZE: usize = 16;
+/// The size of a cache line, which is the stride of the descriptors on non-coherent platforms.
+pub(super) const CACHE_LINE_SIZE: usize = 64;
+
+// The bits of the third word of the transmit descriptors.
+pub(super) const TDES2_IOC: u32 = 1 << 31;
+pub(super) const TDES2_B1L_MASK: u32 = 0x3fff;
+
+// The bits of the fourth word of the transmit descriptors.
+pub(super) const TDES3_OWN: u32 = 1 << 31;
+pub(super) const TDES3_FD: u32 = 1 << 29;
+pub(super) const TDES3_LD: u32 = 1 << 28;
+pub(super) const TDES3_ES: u32 = 1 << 15;
+pub(super) const TDES3_FL_MASK: u32 = 0x7fff;
+
+// The bits of the fourth word of the receive descriptors.
+pub(super) const RDES3_OWN: u32 = 1 << 31;
+pub(super) const RDES3_IOC: u32 = 1 << 30;
+pub(super) const RDES3_BUF1V: u32 = 1 << 24;
+pub(super) const RDES3_CTXT: u32 = 1 << 30;
+pub(super) const RDES3_FD: u32 = 1 << 29;
+pub(super) const RDES3_LD: u32 = 1 << 28;
+pub(super) const RDES3_ES: u32 = 1 << 15;
+pub(super) const RDES3_PL_MASK: u32 = 0x7fff;
+
+/// A normal descriptor of the DWMAC 4.x and 5.x controllers.
+///
+/// The meaning of the words depends on the direction and on whether the descriptor is
+/// written by the driver (the read format) or by the controller (the write-back format).
+#[derive(Clone, Copy, Default, Debug, Pod)]
+#[repr(C)]
+pub(super) struct Descriptor {
+    pub des0: u32,
+    pub des1: u32,
+    pub des2: u32,
+    pub des3: u32,
+}
+
+impl Descriptor {
+    /// Creates a descriptor whose first buffer is at `daddr`.
+    pub fn with_buffer(daddr: Daddr, des2: u32, des3: u32) -> Self {
+        Self {
+            des0: daddr as u32,
+            des1: (daddr as u64 >> 32) as u32,
+            des2,
+            des3,
+        }
+    }
+}
+
+/// A ring of descriptors.
+pub(super) struct DescRing {
+    storage: DmaStream,
+    len: usize,
+    stride: usize,
+}
+
+impl DescRing {
+    /// Allocates a ring of `len` descriptors, which are owned by the driver.
+    pub fn new(len: usize, is_cache_coherent: bool) -> Result<Self, ostd::Error> {
+        let stride = if is_cache_coherent {
+            DESC_SIZE
+        } else {
+            CACHE_LINE_SIZE
+        };
+        let nframes = (len * stride).div_ceil(PAGE_SIZE);
+        let segment = FrameAllocOptions::new().alloc_segment(nframes)?;
+        let storage = DmaStream::map(
+            segment.into(),
+            DmaDirection::Bidirectional,
+            is_cache_coherent,
+        )
+        .map_err(|_| ostd::Error::AccessDenied)?;
+        storage.sync(0..storage.nbytes())?;
+        Ok(Self {
+            storage,
+            len,
+            stride,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns the number of bytes between two descriptors.
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    /// Returns the device address of the descriptor at `index`.
+    ///
+    /// The index may be equal to the length, which is the end of the ring.
+    pub fn daddr_of(&self, index: usize) -> Daddr {
+        debug_assert!(index <= self.len);
+        self.storage.daddr() + index * self.stride
+    }
+
+    /// Reads the descriptor at `index`, discarding the stale copy in the CPU caches.
+    pub fn read(&self, index: usize) -> Descriptor {
+        let offset = index * self.stride;
+        self.storage.sync(offset..offset + DESC_SIZE).unwrap();
+        self.storage.read_val(offset).unwrap()
+    }
+
+    /// Writes the descriptor at `index`, and makes it visible to the controller.
+    pub fn write(&self, index: usize, desc: &Descriptor) {
+        let offset = index * self.stride;
+        self.storage.write_val(offset, desc).unwrap();
+        self.storage.sync(offset..offset + DESC_SIZE).unwrap();
+    }
+}
+
+impl HasDaddr for DescRing {
+    fn daddr(&self) -> Daddr {
+        self.storage.daddr()
+    }
+}
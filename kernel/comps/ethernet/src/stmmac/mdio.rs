@@ -0,0 +1,107 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The MDIO controller that is built into stmmac controllers.
+
+use alloc::{string::String, sync::Arc};
+
+use ostd::{
+    io::MmioRegion,
+    mm::VmIoOnce,
+    sync::{LocalIrqDisabled, SpinLock},
+};
+
+use super::regs;
+use crate::{
+    mdio::{MdioBus, MdioError, NR_PHY_ADDRS},
+    poll_until,
+};
+
+// The bits of the MDIO address register.
+const MDIO_ADDR_GB: u32 = 1 << 0;
+const MDIO_ADDR_GOC_WRITE: u32 = 0b01 << 2;
+const MDIO_ADDR_GOC_READ: u32 = 0b11 << 2;
+const MDIO_ADDR_CR_SHIFT: u32 = 8;
+const MDIO_ADDR_RDA_SHIFT: u32 = 16;
+const MDIO_ADDR_PA_SHIFT: u32 = 21;
+
+/// The divider of the CSR clock that generates MDC.
+///
+/// The largest standard divider (CSR clock / 124) keeps MDC under 2.5 MHz for any CSR clock up
+/// to 300 MHz, which covers all the known integrations.
+const MDIO_CLOCK_RANGE: u32 = 5;
+
+/// The time (in microseconds) that an MDIO transaction takes at most.
+const MDIO_TIMEOUT_US: u64 = 10_000;
+
+/// The MDIO bus of an stmmac controller.
+pub(super) struct StmmacMdio {
+    name: String,
+    io_mem: Arc<MmioRegion>,
+    /// Serializes the transactions, which use the same pair of registers.
+    lock: SpinLock<(), LocalIrqDisabled>,
+}
+
+impl StmmacMdio {
+    pub fn new(name: String, io_mem: Arc<MmioRegion>) -> Self {
+        Self {
+            name,
+            io_mem,
+            lock: SpinLock::new(()),
+        }
+    }
+
+    fn wait_idle(&self) -> Result<(), MdioError> {
+        poll_until(MDIO_TIMEOUT_US, || {
+            let addr: u32 = self.io_mem.read_once(regs::MAC_MDIO_ADDRESS).unwrap();
+            (addr & MDIO_ADDR_GB == 0).then_some(())
+        })
+        .ok_or(MdioError::Timeout)
+    }
+
+    fn start(&self, phy_addr: u8, reg: u8, op: u32) {
+        let addr = ((phy_addr as u32) << MDIO_ADDR_PA_SHIFT)
+            | ((reg as u32) << MDIO_ADDR_RDA_SHIFT)
+            | (MDIO_CLOCK_RANGE << MDIO_ADDR_CR_SHIFT)
+            | op
+            | MDIO_ADDR_GB;
+        self.io_mem
+            .write_once(regs::MAC_MDIO_ADDRESS, &addr)
+            .unwrap();
+    }
+}
+
+fn check_args(phy_addr: u8, reg: u8) -> Result<(), MdioError> {
+    if phy_addr >= NR_PHY_ADDRS || reg >= 32 {
+        return Err(MdioError::InvalidArgs);
+    }
+    Ok(())
+}
+
+impl MdioBus for StmmacMdio {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn read(&self, phy_addr: u8, reg: u8) -> Result<u16, MdioError> {
+        check_args(phy_addr, reg)?;
+        let _guard = self.lock.lock();
+
+        self.wait_idle()?;
+        self.start(phy_addr, reg, MDIO_ADDR_GOC_READ);
+        self.wait_idle()?;
+        let data: u32 = self.io_mem.read_once(regs::MAC_MDIO_DATA).unwrap();
+        Ok(data as u16)
+    }
+
+    fn write(&self, phy_addr: u8, reg: u8, value: u16) -> Result<(), MdioError> {
+        check_args(phy_addr, reg)?;
+        let _guard = self.lock.lock();
+
+        self.wait_idle()?;
+        self.io_mem
+            .write_once(regs::MAC_MDIO_DATA, &(value as u32))
+            .unwrap();
+        self.start(phy_addr, reg, MDIO_ADDR_GOC_WRITE);
+        self.wait_idle()
+    }
+}
@@ -0,0 +1,540 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The Synopsys DesignWare Ethernet QoS controller (stmmac) driver.
+//!
+//! This driver supports the DWMAC 4.x and 5.x controllers, e.g., the GMACs of the StarFive
+//! JH7110 SoC (VisionFive 2). Only the first DMA channel and the first MTL queue are used.
+//! Packets are transferred with one descriptor each, and the checksums are computed by the
+//! network stack.
+//!
+//! The clocks and the resets of the controller, including the RGMII transmit clock that
+//! depends on the link speed on some SoCs, are expected to be set up by the firmware.
+
+mod desc;
+mod mdio;
+
+use alloc::{collections::linked_list::LinkedList, string::String, sync::Arc, vec::Vec};
+use core::fmt::Debug;
+
+use aster_bigtcp::device::{Checksum, DeviceCapabilities, Medium};
+use aster_network::{
+    AnyNetworkDevice, EthernetAddr, RxBuffer, TxBuffer, VirtioNetError, RX_BUFFER_POOL,
+};
+use log::debug;
+use ostd::{
+    io::MmioRegion,
+    mm::{DmaStream, HasDaddr, VmIoOnce},
+    sync::{LocalIrqDisabled, SpinLock},
+};
+
+use self::desc::{
+    DescRing, Descriptor, DESC_SIZE, RDES3_BUF1V, RDES3_CTXT, RDES3_ES, RDES3_FD, RDES3_IOC,
+    RDES3_LD, RDES3_OWN, RDES3_PL_MASK, TDES2_B1L_MASK, TDES2_IOC, TDES3_ES, TDES3_FD,
+    TDES3_FL_MASK, TDES3_LD, TDES3_OWN,
+};
+pub(crate) use self::mdio::StmmacMdio;
+use crate::{
+    phy::{Duplex, Link, Speed},
+    poll_until, EthernetMac,
+};
+
+/// The register offsets of the DWMAC 4.x and 5.x controllers.
+mod regs {
+    pub const MAC_CONFIGURATION: usize = 0x0000;
+    pub const MAC_PACKET_FILTER: usize = 0x0008;
+    pub const MAC_RXQ_CTRL0: usize = 0x00a0;
+    pub const MAC_VERSION: usize = 0x0110;
+    pub const MAC_HW_FEATURE1: usize = 0x0120;
+    pub const MAC_MDIO_ADDRESS: usize = 0x0200;
+    pub const MAC_MDIO_DATA: usize = 0x0204;
+    pub const MAC_ADDRESS0_HIGH: usize = 0x0300;
+    pub const MAC_ADDRESS0_LOW: usize = 0x0304;
+    pub const MTL_TXQ0_OPERATION_MODE: usize = 0x0d00;
+    pub const MTL_RXQ0_OPERATION_MODE: usize = 0x0d30;
+    pub const DMA_MODE: usize = 0x1000;
+    pub const DMA_SYSBUS_MODE: usize = 0x1004;
+    pub const DMA_CH0_CONTROL: usize = 0x1100;
+    pub const DMA_CH0_TX_CONTROL: usize = 0x1104;
+    pub const DMA_CH0_RX_CONTROL: usize = 0x1108;
+    pub const DMA_CH0_TXDESC_LIST_HADDR: usize = 0x1110;
+    pub const DMA_CH0_TXDESC_LIST_ADDR: usize = 0x1114;
+    pub const DMA_CH0_RXDESC_LIST_HADDR: usize = 0x1118;
+    pub const DMA_CH0_RXDESC_LIST_ADDR: usize = 0x111c;
+    pub const DMA_CH0_TXDESC_TAIL_POINTER: usize = 0x1120;
+    pub const DMA_CH0_RXDESC_TAIL_POINTER: usize = 0x1128;
+    pub const DMA_CH0_TXDESC_RING_LENGTH: usize = 0x112c;
+    pub const DMA_CH0_RXDESC_RING_LENGTH: usize = 0x1130;
+    pub const DMA_CH0_INTERRUPT_ENABLE: usize = 0x1134;
+    pub const DMA_CH0_STATUS: usize = 0x1160;
+}
+
+// The bits of the MAC configuration register.
+const MAC_CONFIG_RE: u32 = 1 << 0;
+const MAC_CONFIG_TE: u32 = 1 << 1;
+const MAC_CONFIG_DM: u32 = 1 << 13;
+const MAC_CONFIG_FES: u32 = 1 << 14;
+const MAC_CONFIG_PS: u32 = 1 << 15;
+const MAC_CONFIG_ACS: u32 = 1 << 20;
+const MAC_CONFIG_CST: u32 = 1 << 21;
+
+// The bits of the MAC packet filter register.
+const MAC_PACKET_FILTER_PM: u32 = 1 << 4;
+
+// The generic (DCB) mode of the receive queue 0.
+const MAC_RXQ0_ENABLE_DCB: u32 = 0b10;
+
+// The fields of the MAC version and hardware feature registers.
+const MAC_VERSION_SNPSVER_MASK: u32 = 0xff;
+const MAC_HW_FEATURE1_TXFIFOSIZE_SHIFT: u32 = 6;
+const MAC_HW_FEATURE1_ADDR64_SHIFT: u32 = 14;
+
+// The bits of the MAC address high register.
+const MAC_ADDRESS_AE: u32 = 1 << 31;
+
+// The bits of the MTL queue operation mode registers.
+const MTL_TXQ_TSF: u32 = 1 << 1;
+const MTL_TXQ_TXQEN: u32 = 0b10 << 2;
+const MTL_TXQ_TQS_SHIFT: u32 = 16;
+const MTL_RXQ_RSF: u32 = 1 << 5;
+const MTL_RXQ_RQS_SHIFT: u32 = 20;
+
+// The bits of the DMA mode register.
+const DMA_MODE_SWR: u32 = 1 << 0;
+
+// The bits of the DMA system bus mode register.
+const DMA_SYSBUS_BLEN4: u32 = 1 << 1;
+const DMA_SYSBUS_BLEN8: u32 = 1 << 2;
+const DMA_SYSBUS_BLEN16: u32 = 1 << 3;
+const DMA_SYSBUS_EAME: u32 = 1 << 11;
+
+// The bits of the DMA channel registers.
+const DMA_CH_CONTROL_DSL_SHIFT: u32 = 18;
+const DMA_CH_TX_CONTROL_ST: u32 = 1 << 0;
+const DMA_CH_TX_CONTROL_OSF: u32 = 1 << 4;
+const DMA_CH_RX_CONTROL_SR: u32 = 1 << 0;
+const DMA_CH_RX_CONTROL_RBSZ_SHIFT: u32 = 1;
+const DMA_CH_PBL_SHIFT: u32 = 16;
+
+/// The programmable burst length of the DMA channel.
+const DMA_PBL: u32 = 8;
+/// The width (in bytes) of the system bus, which is the unit of the descriptor skip length.
+const DMA_BUS_WIDTH: usize = 8;
+
+/// The time (in microseconds) that a software reset takes at most.
+const RESET_TIMEOUT_US: u64 = 100_000;
+
+/// The number of transmit descriptors.
+const TX_RING_LEN: usize = 64;
+/// The number of receive descriptors.
+const RX_RING_LEN: usize = 64;
+/// The size of the receive buffers given to the controller, which fits a full-sized frame.
+const RX_BUFFER_SIZE: usize = 1536;
+/// The maximum transmission unit, which includes the Ethernet header.
+const MTU: usize = 1514;
+
+/// The errors of stmmac initialization.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum StmmacError {
+    /// The controller is not a DWMAC 4.x or 5.x controller.
+    Unsupported(u32),
+    /// The software reset does not complete, which usually means that the clocks are off.
+    ResetTimeout,
+    /// The descriptor rings cannot be allocated.
+    NoMemory,
+}
+
+/// An stmmac controller.
+pub(crate) struct Stmmac {
+    name: String,
+    io_mem: Arc<MmioRegion>,
+    mac_addr: EthernetAddr,
+    caps: DeviceCapabilities,
+    tx_ring: DescRing,
+    rx_ring: DescRing,
+    tx_buffers: Vec<Option<TxBuffer>>,
+    rx_buffers: Vec<Option<RxBuffer>>,
+    /// The next transmit descriptor to be used.
+    tx_head: usize,
+    /// The next transmit descriptor to be reclaimed.
+    tx_tail: usize,
+    /// The next receive descriptor to be checked.
+    rx_next: usize,
+    link: Option<Link>,
+}
+
+impl Stmmac {
+    /// Resets and initializes the controller.
+    ///
+    /// The controller neither sends nor receives packets until the link is up.
+    pub fn new(
+        name: String,
+        io_mem: Arc<MmioRegion>,
+        mac_addr: Option<EthernetAddr>,
+        is_cache_coherent: bool,
+    ) -> Result<Self, StmmacError> {
+        let version = io_mem.read_once::<u32>(regs::MAC_VERSION).unwrap();
+        let snps_version = version & MAC_VERSION_SNPSVER_MASK;
+        if !(0x40..0x60).contains(&snps_version) {
+            return Err(StmmacError::Unsupported(snps_version));
+        }
+
+        // The MAC address may have been programmed by the firmware, which is lost by the reset.
+        let mac_addr = mac_addr.unwrap_or_else(|| read_mac_addr(&io_mem));
+
+        io_mem.write_once(regs::DMA_MODE, &DMA_MODE_SWR).unwrap();
+        poll_until(RESET_TIMEOUT_US, || {
+            let mode: u32 = io_mem.read_once(regs::DMA_MODE).unwrap();
+            (mode & DMA_MODE_SWR == 0).then_some(())
+        })
+        .ok_or(StmmacError::ResetTimeout)?;
+
+        let tx_ring =
+            DescRing::new(TX_RING_LEN, is_cache_coherent).map_err(|_| StmmacError::NoMemory)?;
+        let rx_ring =
+            DescRing::new(RX_RING_LEN, is_cache_coherent).map_err(|_| StmmacError::NoMemory)?;
+
+        let mut device = Self {
+            name,
+            io_mem,
+            mac_addr,
+            caps: init_caps(),
+            tx_ring,
+            rx_ring,
+            tx_buffers: (0..TX_RING_LEN).map(|_| None).collect(),
+            rx_buffers: (0..RX_RING_LEN).map(|_| None).collect(),
+            tx_head: 0,
+            tx_tail: 0,
+            rx_next: 0,
+            link: None,
+        };
+        for index in 0..RX_RING_LEN {
+            device.refill_rx(index);
+        }
+        device.init_dma();
+        device.init_mtl();
+        device.init_mac();
+        Ok(device)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn read_reg(&self, offset: usize) -> u32 {
+        self.io_mem.read_once(offset).unwrap()
+    }
+
+    fn write_reg(&self, offset: usize, value: u32) {
+        self.io_mem.write_once(offset, &value).unwrap();
+    }
+
+    fn init_dma(&self) {
+        let hw_feature1 = self.read_reg(regs::MAC_HW_FEATURE1);
+        let mut sysbus_mode = DMA_SYSBUS_BLEN4 | DMA_SYSBUS_BLEN8 | DMA_SYSBUS_BLEN16;
+        if (hw_feature1 >> MAC_HW_FEATURE1_ADDR64_SHIFT) & 0b11 != 0 {
+            // Enable the addresses wider than 32 bits.
+            sysbus_mode |= DMA_SYSBUS_EAME;
+        }
+        self.write_reg(regs::DMA_SYSBUS_MODE, sysbus_mode);
+
+        // The padding between the descriptors is skipped on non-coherent platforms.
+        let skip_len = ((self.rx_ring.stride() - DESC_SIZE) / DMA_BUS_WIDTH) as u32;
+        self.write_reg(regs::DMA_CH0_CONTROL, skip_len << DMA_CH_CONTROL_DSL_SHIFT);
+        self.write_reg(
+            regs::DMA_CH0_TX_CONTROL,
+            (DMA_PBL << DMA_CH_PBL_SHIFT) | DMA_CH_TX_CONTROL_OSF,
+        );
+        self.write_reg(
+            regs::DMA_CH0_RX_CONTROL,
+            (DMA_PBL << DMA_CH_PBL_SHIFT)
+                | ((RX_BUFFER_SIZE as u32) << DMA_CH_RX_CONTROL_RBSZ_SHIFT),
+        );
+
+        let tx_base = self.tx_ring.daddr() as u64;
+        let rx_base = self.rx_ring.daddr() as u64;
+        self.write_reg(regs::DMA_CH0_TXDESC_LIST_HADDR, (tx_base >> 32) as u32);
+        self.write_reg(regs::DMA_CH0_TXDESC_LIST_ADDR, tx_base as u32);
+        self.write_reg(regs::DMA_CH0_RXDESC_LIST_HADDR, (rx_base >> 32) as u32);
+        self.write_reg(regs::DMA_CH0_RXDESC_LIST_ADDR, rx_base as u32);
+        self.write_reg(
+            regs::DMA_CH0_TXDESC_RING_LENGTH,
+            self.tx_ring.len() as u32 - 1,
+        );
+        self.write_reg(
+            regs::DMA_CH0_RXDESC_RING_LENGTH,
+            self.rx_ring.len() as u32 - 1,
+        );
+        self.write_reg(
+            regs::DMA_CH0_TXDESC_TAIL_POINTER,
+            self.tx_ring.daddr_of(0) as u32,
+        );
+        self.write_reg(
+            regs::DMA_CH0_RXDESC_TAIL_POINTER,
+            self.rx_ring.daddr_of(self.rx_ring.len()) as u32,
+        );
+
+        // The controller is polled, so the interrupts are disabled.
+        self.write_reg(regs::DMA_CH0_INTERRUPT_ENABLE, 0);
+        self.write_reg(regs::DMA_CH0_STATUS, u32::MAX);
+    }
+
+    fn init_mtl(&self) {
+        // The FIFOs are given to the only queues in the store-and-forward mode.
+        let hw_feature1 = self.read_reg(regs::MAC_HW_FEATURE1);
+        let tx_fifo_size = 128 << ((hw_feature1 >> MAC_HW_FEATURE1_TXFIFOSIZE_SHIFT) & 0x1f);
+        let rx_fifo_size = 128 << (hw_feature1 & 0x1f);
+        let tx_queue_size = (tx_fifo_size / 256).max(1) - 1;
+        let rx_queue_size = (rx_fifo_size / 256).max(1) - 1;
+        self.write_reg(
+            regs::MTL_TXQ0_OPERATION_MODE,
+            MTL_TXQ_TSF | MTL_TXQ_TXQEN | (tx_queue_size << MTL_TXQ_TQS_SHIFT),
+        );
+        self.write_reg(
+            regs::MTL_RXQ0_OPERATION_MODE,
+            MTL_RXQ_RSF | (rx_queue_size << MTL_RXQ_RQS_SHIFT),
+        );
+    }
+
+    fn init_mac(&self) {
+        let addr = self.mac_addr.0;
+        self.write_reg(
+            regs::MAC_ADDRESS0_HIGH,
+            MAC_ADDRESS_AE | ((addr[5] as u32) << 8) | addr[4] as u32,
+        );
+        self.write_reg(
+            regs::MAC_ADDRESS0_LOW,
+            u32::from_le_bytes([addr[0], addr[1], addr[2], addr[3]]),
+        );
+
+        // The multicast packets are needed by IPv6.
+        self.write_reg(regs::MAC_PACKET_FILTER, MAC_PACKET_FILTER_PM);
+        self.write_reg(regs::MAC_RXQ_CTRL0, MAC_RXQ0_ENABLE_DCB);
+        // Strip the FCS of the received packets.
+        self.write_reg(regs::MAC_CONFIGURATION, MAC_CONFIG_ACS | MAC_CONFIG_CST);
+    }
+
+    /// Gives a new buffer to the receive descriptor at `index`.
+    fn refill_rx(&mut self, index: usize) {
+        let rx_pool = RX_BUFFER_POOL.get().unwrap();
+        let rx_buffer = RxBuffer::new(0, rx_pool);
+        rx_buffer.sync_for_device();
+        self.rx_ring.write(
+            index,
+            &Descriptor::with_buffer(rx_buffer.daddr(), 0, RDES3_OWN | RDES3_IOC | RDES3_BUF1V),
+        );
+        self.rx_buffers[index] = Some(rx_buffer);
+    }
+
+    /// Gives the receive descriptor at `index` back to the controller with the same buffer.
+    fn recycle_rx(&mut self, index: usize) {
+        let rx_buffer = self.rx_buffers[index].as_ref().unwrap();
+        rx_buffer.sync_for_device();
+        self.rx_ring.write(
+            index,
+            &Descriptor::with_buffer(rx_buffer.daddr(), 0, RDES3_OWN | RDES3_IOC | RDES3_BUF1V),
+        );
+    }
+
+    /// Tells the controller that the receive descriptors up to `index` are available.
+    fn advance_rx_tail(&self, index: usize) {
+        let tail = self.rx_ring.daddr_of((index + 1) % self.rx_ring.len());
+        self.write_reg(regs::DMA_CH0_RXDESC_TAIL_POINTER, tail as u32);
+    }
+
+    fn receive(&mut self) -> Result<RxBuffer, VirtioNetError> {
+        loop {
+            let index = self.rx_next;
+            let desc = self.rx_ring.read(index);
+            if desc.des3 & RDES3_OWN != 0 {
+                return Err(VirtioNetError::NotReady);
+            }
+            self.rx_next = (index + 1) % self.rx_ring.len();
+
+            // A packet must fit in one buffer, so a packet that spans several descriptors
+            // is a jumbo frame, which is dropped.
+            let is_valid = desc.des3 & RDES3_CTXT == 0
+                && desc.des3 & (RDES3_FD | RDES3_LD) == RDES3_FD | RDES3_LD
+                && desc.des3 & RDES3_ES == 0;
+            let len = (desc.des3 & RDES3_PL_MASK) as usize;
+            if !is_valid || len > RX_BUFFER_SIZE {
+                debug!(
+                    "[Ethernet]: {}: dropped a received packet, status = {:#x}",
+                    self.name, desc.des3
+                );
+                self.recycle_rx(index);
+                self.advance_rx_tail(index);
+                continue;
+            }
+
+            let mut rx_buffer = self.rx_buffers[index].take().unwrap();
+            rx_buffer.set_packet_len(len);
+            self.refill_rx(index);
+            self.advance_rx_tail(index);
+            return Ok(rx_buffer);
+        }
+    }
+
+    fn send(&mut self, packet: &[u8]) -> Result<(), VirtioNetError> {
+        if !self.can_send() {
+            return Err(VirtioNetError::Busy);
+        }
+        if packet.len() > MTU {
+            return Err(VirtioNetError::Unknown);
+        }
+
+        let tx_buffer = TxBuffer::new(&[0u8; 0], packet, &TX_BUFFER_POOL);
+        let len = tx_buffer.nbytes() as u32;
+        let index = self.tx_head;
+        self.tx_ring.write(
+            index,
+            &Descriptor::with_buffer(
+                tx_buffer.daddr(),
+                TDES2_IOC | (len & TDES2_B1L_MASK),
+                TDES3_OWN | TDES3_FD | TDES3_LD | (len & TDES3_FL_MASK),
+            ),
+        );
+        self.tx_buffers[index] = Some(tx_buffer);
+        self.tx_head = (index + 1) % self.tx_ring.len();
+
+        let tail = self.tx_ring.daddr_of(self.tx_head);
+        self.write_reg(regs::DMA_CH0_TXDESC_TAIL_POINTER, tail as u32);
+        Ok(())
+    }
+
+    fn free_processed_tx_buffers(&mut self) {
+        while self.tx_tail != self.tx_head {
+            let desc = self.tx_ring.read(self.tx_tail);
+            if desc.des3 & TDES3_OWN != 0 {
+                break;
+            }
+            if desc.des3 & TDES3_ES != 0 {
+                debug!(
+                    "[Ethernet]: {}: failed to send a packet, status = {:#x}",
+                    self.name, desc.des3
+                );
+            }
+            self.tx_buffers[self.tx_tail] = None;
+            self.tx_tail = (self.tx_tail + 1) % self.tx_ring.len();
+        }
+    }
+
+    fn can_send(&self) -> bool {
+        // One descriptor is kept unused to tell a full ring from an empty one.
+        (self.tx_head + 1) % self.tx_ring.len() != self.tx_tail
+    }
+}
+
+fn read_mac_addr(io_mem: &MmioRegion) -> EthernetAddr {
+    let high: u32 = io_mem.read_once(regs::MAC_ADDRESS0_HIGH).unwrap();
+    let low: u32 = io_mem.read_once(regs::MAC_ADDRESS0_LOW).unwrap();
+    let low = low.to_le_bytes();
+    EthernetAddr([
+        low[0],
+        low[1],
+        low[2],
+        low[3],
+        high as u8,
+        (high >> 8) as u8,
+    ])
+}
+
+fn init_caps() -> DeviceCapabilities {
+    let mut caps = DeviceCapabilities::default();
+
+    caps.max_burst_size = None;
+    caps.medium = Medium::Ethernet;
+    caps.max_transmission_unit = MTU;
+
+    // We do not use the checksum offloading.
+    caps.checksum.tcp = Checksum::Both;
+    caps.checksum.udp = Checksum::Both;
+    caps.checksum.ipv4 = Checksum::Both;
+    caps.checksum.icmpv4 = Checksum::Both;
+
+    caps
+}
+
+impl AnyNetworkDevice for Stmmac {
+    fn mac_addr(&self) -> EthernetAddr {
+        self.mac_addr
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        self.caps.clone()
+    }
+
+    fn can_receive(&self) -> bool {
+        self.rx_ring.read(self.rx_next).des3 & RDES3_OWN == 0
+    }
+
+    fn can_send(&self) -> bool {
+        self.can_send()
+    }
+
+    fn receive(&mut self) -> Result<RxBuffer, VirtioNetError> {
+        self.receive()
+    }
+
+    fn send(&mut self, packet: &[u8]) -> Result<(), VirtioNetError> {
+        self.send(packet)
+    }
+
+    fn free_processed_tx_buffers(&mut self) {
+        self.free_processed_tx_buffers();
+    }
+
+    fn notify_poll_end(&mut self) {}
+}
+
+impl EthernetMac for Stmmac {
+    fn adjust_link(&mut self, link: Option<Link>) {
+        self.link = link;
+
+        let mut config = self.read_reg(regs::MAC_CONFIGURATION)
+            & !(MAC_CONFIG_RE | MAC_CONFIG_TE | MAC_CONFIG_DM | MAC_CONFIG_FES | MAC_CONFIG_PS);
+        let tx_control = self.read_reg(regs::DMA_CH0_TX_CONTROL);
+        let rx_control = self.read_reg(regs::DMA_CH0_RX_CONTROL);
+
+        let Some(link) = link else {
+            self.write_reg(regs::MAC_CONFIGURATION, config);
+            self.write_reg(regs::DMA_CH0_TX_CONTROL, tx_control & !DMA_CH_TX_CONTROL_ST);
+            self.write_reg(regs::DMA_CH0_RX_CONTROL, rx_control & !DMA_CH_RX_CONTROL_SR);
+            return;
+        };
+
+        config |= match link.speed {
+            Speed::Mbps1000 => 0,
+            Speed::Mbps100 => MAC_CONFIG_PS | MAC_CONFIG_FES,
+            Speed::Mbps10 => MAC_CONFIG_PS,
+        };
+        if link.duplex == Duplex::Full {
+            config |= MAC_CONFIG_DM;
+        }
+        self.write_reg(regs::DMA_CH0_TX_CONTROL, tx_control | DMA_CH_TX_CONTROL_ST);
+        self.write_reg(regs::DMA_CH0_RX_CONTROL, rx_control | DMA_CH_RX_CONTROL_SR);
+        self.write_reg(
+            regs::MAC_CONFIGURATION,
+            config | MAC_CONFIG_RE | MAC_CONFIG_TE,
+        );
+    }
+
+    fn has_completed_tx(&self) -> bool {
+        self.tx_tail != self.tx_head && self.tx_ring.read(self.tx_tail).des3 & TDES3_OWN == 0
+    }
+}
+
+impl Debug for Stmmac {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Stmmac")
+            .field("name", &self.name)
+            .field("mac_addr", &self.mac_addr)
+            .field("link", &self.link)
+            .field("tx_head", &self.tx_head)
+            .field("tx_tail", &self.tx_tail)
+            .field("rx_next", &self.rx_next)
+            .finish()
+    }
+}
+
+static TX_BUFFER_POOL: SpinLock<LinkedList<DmaStream>, LocalIrqDisabled> =
+    SpinLock::new(LinkedList::new());
@@ -0,0 +1,223 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The registered GPIO chips and the requested lines.
+
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+
+use ostd::sync::{LocalIrqDisabled, SpinLock};
+
+use crate::{GpioDirection, GpioDriver, GpioError, GpioIrqTrigger};
+
+type IrqHandler = Arc<dyn Fn() + Send + Sync>;
+
+/// A registered GPIO chip.
+pub struct GpioChip {
+    index: usize,
+    base: u32,
+    driver: Arc<dyn GpioDriver>,
+    phandle: Option<u32>,
+    lines: SpinLock<Vec<LineState>, LocalIrqDisabled>,
+}
+
+#[derive(Default)]
+struct LineState {
+    /// The label of the user, if the line is requested
+    label: Option<String>,
+    is_active_low: bool,
+    irq_handler: Option<IrqHandler>,
+}
+
+impl GpioChip {
+    pub(crate) fn new(
+        index: usize,
+        base: u32,
+        driver: Arc<dyn GpioDriver>,
+        phandle: Option<u32>,
+    ) -> Self {
+        let lines = (0..driver.ngpio()).map(|_| LineState::default()).collect();
+        Self {
+            index,
+            base,
+            driver,
+            phandle,
+            lines: SpinLock::new(lines),
+        }
+    }
+
+    /// Returns the index of the chip.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the global number of the first line.
+    pub fn base(&self) -> u32 {
+        self.base
+    }
+
+    /// Returns the number of the lines.
+    pub fn ngpio(&self) -> u32 {
+        self.driver.ngpio()
+    }
+
+    /// Returns the label of the chip.
+    pub fn label(&self) -> &str {
+        self.driver.label()
+    }
+
+    pub(crate) fn phandle(&self) -> Option<u32> {
+        self.phandle
+    }
+
+    /// Returns the label of the user of the line, if the line is requested.
+    pub fn line_label(&self, offset: u32) -> Option<String> {
+        self.lines.lock().get(offset as usize)?.label.clone()
+    }
+
+    /// Requests the line with the offset in the chip.
+    ///
+    /// This method fails with [`GpioError::Busy`] if the line has been requested.
+    pub fn request(self: &Arc<Self>, offset: u32, label: &str) -> Result<GpioDesc, GpioError> {
+        let mut lines = self.lines.lock();
+        let line = lines
+            .get_mut(offset as usize)
+            .ok_or(GpioError::InvalidArgs)?;
+        if line.label.is_some() {
+            return Err(GpioError::Busy);
+        }
+        *line = LineState {
+            label: Some(label.to_string()),
+            ..Default::default()
+        };
+
+        Ok(GpioDesc {
+            chip: self.clone(),
+            offset,
+        })
+    }
+
+    /// Handles an interrupt of the line.
+    ///
+    /// This method should be called by the driver in the interrupt context.
+    pub fn handle_irq(&self, offset: u32) {
+        let handler = self
+            .lines
+            .lock()
+            .get(offset as usize)
+            .and_then(|line| line.irq_handler.clone());
+        if let Some(handler) = handler {
+            handler();
+        }
+    }
+}
+
+/// A requested GPIO line.
+///
+/// The values are logical, i.e., a value of `true` is the low level if the line is active-low.
+/// The line is released when the descriptor is dropped.
+pub struct GpioDesc {
+    chip: Arc<GpioChip>,
+    offset: u32,
+}
+
+impl GpioDesc {
+    /// Returns the chip of the line.
+    pub fn chip(&self) -> &Arc<GpioChip> {
+        &self.chip
+    }
+
+    /// Returns the offset of the line in the chip.
+    pub fn offset(&self) -> u32 {
+        self.offset
+    }
+
+    /// Returns the global number of the line.
+    pub fn number(&self) -> u32 {
+        self.chip.base + self.offset
+    }
+
+    /// Returns whether the line is active-low.
+    pub fn is_active_low(&self) -> bool {
+        self.chip.lines.lock()[self.offset as usize].is_active_low
+    }
+
+    /// Sets whether the line is active-low.
+    pub fn set_active_low(&self, is_active_low: bool) {
+        self.chip.lines.lock()[self.offset as usize].is_active_low = is_active_low;
+    }
+
+    /// Returns the direction of the line.
+    pub fn direction(&self) -> GpioDirection {
+        self.chip.driver.direction(self.offset)
+    }
+
+    /// Makes the line an input.
+    pub fn direction_input(&self) -> Result<(), GpioError> {
+        self.chip.driver.set_input(self.offset)
+    }
+
+    /// Makes the line an output, which is driven to the value.
+    pub fn direction_output(&self, value: bool) -> Result<(), GpioError> {
+        let value = value ^ self.is_active_low();
+        self.chip.driver.set_output(self.offset, value)
+    }
+
+    /// Returns the value of the line.
+    pub fn get_value(&self) -> bool {
+        self.chip.driver.get_value(self.offset) ^ self.is_active_low()
+    }
+
+    /// Sets the value of the line, which is an output.
+    pub fn set_value(&self, value: bool) {
+        let value = value ^ self.is_active_low();
+        self.chip.driver.set_value(self.offset, value)
+    }
+
+    /// Makes the line an input, and handles its interrupts with the handler.
+    ///
+    /// The handler is called in the interrupt context, so it must not sleep. The trigger is
+    /// physical, i.e., it is not inverted if the line is active-low.
+    /// A level-triggered interrupt is raised again as long as the level holds, so the handler
+    /// should stop the device from holding it, or call [`Self::free_irq`].
+    pub fn request_irq<F>(&self, trigger: GpioIrqTrigger, handler: F) -> Result<(), GpioError>
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        {
+            let mut lines = self.chip.lines.lock();
+            let line = &mut lines[self.offset as usize];
+            if line.irq_handler.is_some() {
+                return Err(GpioError::Busy);
+            }
+            line.irq_handler = Some(Arc::new(handler));
+        }
+
+        let result = self
+            .direction_input()
+            .and_then(|_| self.chip.driver.set_irq_trigger(self.offset, Some(trigger)));
+        if result.is_err() {
+            self.chip.lines.lock()[self.offset as usize].irq_handler = None;
+        }
+        result
+    }
+
+    /// Stops handling the interrupts of the line.
+    pub fn free_irq(&self) {
+        let handler = self.chip.lines.lock()[self.offset as usize]
+            .irq_handler
+            .take();
+        if handler.is_some() {
+            let _ = self.chip.driver.set_irq_trigger(self.offset, None);
+        }
+    }
+}
+
+impl Drop for GpioDesc {
+    fn drop(&mut self) {
+        self.free_irq();
+        self.chip.lines.lock()[self.offset as usize] = LineState::default();
+    }
+}
@@ -0,0 +1,48 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The GPIO lines in the device tree.
+
+use alloc::format;
+
+use fdt::node::FdtNode;
+
+use crate::{find_chip_by_phandle, GpioDesc, GpioError};
+
+/// The flag of the specifier that means the line is active-low.
+const GPIO_ACTIVE_LOW: u32 = 1 << 0;
+
+/// Requests the GPIO line of the device node.
+///
+/// The line is the `index`-th specifier of `<con_id>-gpios` (or `gpios` if `con_id` is
+/// `None`), which is the phandle of the chip, the offset and the flags (i.e., the
+/// `#gpio-cells` of the chip must be two). The line is active-low if the flags say so.
+pub fn request_fdt_gpio(
+    node: &FdtNode,
+    con_id: Option<&str>,
+    index: usize,
+    label: &str,
+) -> Result<GpioDesc, GpioError> {
+    let value = match con_id {
+        Some(con_id) => {
+            let name = format!("{}-gpios", con_id);
+            node.property(&name)
+        }
+        None => node.property("gpios"),
+    }
+    .ok_or(GpioError::InvalidArgs)?
+    .value;
+
+    let cell = |i: usize| {
+        let bytes = value.get((index * 3 + i) * 4..(index * 3 + i) * 4 + 4)?;
+        Some(u32::from_be_bytes(bytes.try_into().unwrap()))
+    };
+    let (Some(phandle), Some(offset), Some(flags)) = (cell(0), cell(1), cell(2)) else {
+        return Err(GpioError::InvalidArgs);
+    };
+
+    // The chip may be registered by a driver that is not probed yet.
+    let chip = find_chip_by_phandle(phandle).ok_or(GpioError::Busy)?;
+    let desc = chip.request(offset, label)?;
+    desc.set_active_low(flags & GPIO_ACTIVE_LOW != 0);
+    Ok(desc)
+}
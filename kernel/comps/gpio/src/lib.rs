@@ -0,0 +1,188 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The GPIO and pin control subsystem of Asterinas.
+//!
+//! Like the gpiolib of Linux, each GPIO controller is driven by a [`GpioDriver`] and
+//! registered as a [`GpioChip`], whose lines are numbered globally from the base of the chip.
+//! A line must be requested as a [`GpioDesc`] before it is used, which sets the direction,
+//! gets or sets the value, and handles the interrupts of the line. The lines that are
+//! described in the device tree (e.g., `reset-gpios`) can be requested by
+//! [`request_fdt_gpio`].
+//!
+//! The pin controllers are registered as [`PinctrlDriver`]s. The `default` states of the
+//! enabled nodes in the device tree are applied when the subsystem is initialized, and the
+//! drivers can apply the other states by [`apply_pinctrl_state`].
+//!
+//! The following drivers are provided:
+//!
+//! - `sifive`: the GPIO controller of the SiFive SoCs, which is discovered through the
+//!   device tree.
+//! - `pinctrl-single`: the pin controllers whose pins are configured by the bits of
+//!   registers, which are discovered through the device tree.
+
+#![no_std]
+#![deny(unsafe_code)]
+
+extern crate alloc;
+
+mod chip;
+#[cfg(target_arch = "riscv64")]
+mod dt;
+#[cfg(target_arch = "riscv64")]
+mod pinctrl;
+#[cfg(target_arch = "riscv64")]
+mod sifive;
+
+use alloc::{sync::Arc, vec::Vec};
+
+use component::{init_component, ComponentInitError};
+use log::info;
+use ostd::sync::{LocalIrqDisabled, SpinLock};
+
+pub use self::chip::{GpioChip, GpioDesc};
+#[cfg(target_arch = "riscv64")]
+pub use self::{
+    dt::request_fdt_gpio,
+    pinctrl::{apply_pinctrl_state, register_pinctrl, PinctrlDriver},
+};
+
+static CHIPS: SpinLock<Vec<Arc<GpioChip>>, LocalIrqDisabled> = SpinLock::new(Vec::new());
+
+#[init_component]
+fn gpio_component_init() -> Result<(), ComponentInitError> {
+    #[cfg(target_arch = "riscv64")]
+    {
+        pinctrl::init();
+        sifive::init();
+    }
+    Ok(())
+}
+
+/// The direction of a GPIO line.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GpioDirection {
+    /// The line is read.
+    Input,
+    /// The line is driven.
+    Output,
+}
+
+/// The trigger of the interrupts of a GPIO line.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GpioIrqTrigger {
+    /// The value changes from low to high.
+    RisingEdge,
+    /// The value changes from high to low.
+    FallingEdge,
+    /// The value changes.
+    BothEdges,
+    /// The value is high.
+    HighLevel,
+    /// The value is low.
+    LowLevel,
+}
+
+/// The errors of GPIO and pin control.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GpioError {
+    /// The line or the parameters are invalid.
+    InvalidArgs,
+    /// The line has been requested, or its interrupt has been taken.
+    Busy,
+    /// The operation is not supported by the hardware.
+    NotSupported,
+    /// The hardware reports an error.
+    IoError,
+}
+
+/// A GPIO controller driver.
+///
+/// The lines are identified by their offsets in the controller, which are less than
+/// [`Self::ngpio`]. The methods may be called in the interrupt context, so they must not
+/// sleep.
+pub trait GpioDriver: Send + Sync {
+    /// Returns the label of the controller.
+    fn label(&self) -> &str;
+
+    /// Returns the number of the lines.
+    fn ngpio(&self) -> u32;
+
+    /// Returns the direction of the line.
+    fn direction(&self, offset: u32) -> GpioDirection;
+
+    /// Makes the line an input.
+    fn set_input(&self, offset: u32) -> Result<(), GpioError>;
+
+    /// Makes the line an output, which is driven to the value.
+    fn set_output(&self, offset: u32, value: bool) -> Result<(), GpioError>;
+
+    /// Returns the (physical) value of the line.
+    fn get_value(&self, offset: u32) -> bool;
+
+    /// Sets the (physical) value of the line, which is an output.
+    fn set_value(&self, offset: u32, value: bool);
+
+    /// Enables the interrupts of the line with the trigger, or disables them with `None`.
+    ///
+    /// The driver should call [`GpioChip::handle_irq`] when an interrupt is raised.
+    fn set_irq_trigger(
+        &self,
+        _offset: u32,
+        _trigger: Option<GpioIrqTrigger>,
+    ) -> Result<(), GpioError> {
+        Err(GpioError::NotSupported)
+    }
+}
+
+/// Registers a GPIO controller driver, returning the registered chip.
+///
+/// The phandle is that of the node of the controller in the device tree, if any. The lines of
+/// the chip are numbered after the lines of the chips that have been registered.
+pub fn register_chip(driver: Arc<dyn GpioDriver>, phandle: Option<u32>) -> Arc<GpioChip> {
+    let mut chips = CHIPS.lock();
+    let base = chips.last().map_or(0, |chip| chip.base() + chip.ngpio());
+    let chip = Arc::new(GpioChip::new(chips.len(), base, driver, phandle));
+    info!(
+        "[GPIO]: registered gpiochip{} ({}) with GPIOs {}-{}",
+        chip.index(),
+        chip.label(),
+        base,
+        base + chip.ngpio().saturating_sub(1)
+    );
+    chips.push(chip.clone());
+    chip
+}
+
+/// Returns the chip with the index.
+pub fn get_chip(index: usize) -> Option<Arc<GpioChip>> {
+    CHIPS.lock().get(index).cloned()
+}
+
+/// Returns all the registered chips.
+pub fn all_chips() -> Vec<Arc<GpioChip>> {
+    CHIPS.lock().clone()
+}
+
+/// Requests the line with the global number.
+///
+/// The label describes the user of the line. This function fails with [`GpioError::Busy`] if
+/// the line has been requested.
+pub fn request_gpio(number: u32, label: &str) -> Result<GpioDesc, GpioError> {
+    let chip = CHIPS
+        .lock()
+        .iter()
+        .find(|chip| (chip.base()..chip.base() + chip.ngpio()).contains(&number))
+        .cloned()
+        .ok_or(GpioError::InvalidArgs)?;
+    let offset = number - chip.base();
+    chip.request(offset, label)
+}
+
+#[cfg(target_arch = "riscv64")]
+fn find_chip_by_phandle(phandle: u32) -> Option<Arc<GpioChip>> {
+    CHIPS
+        .lock()
+        .iter()
+        .find(|chip| chip.phandle() == Some(phandle))
+        .cloned()
+}
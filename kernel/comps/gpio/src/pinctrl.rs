@@ -0,0 +1,214 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The pin controllers, which select the functions of the pins (i.e., pinmux) and configure
+//! them.
+//!
+//! The states of a device node are given by `pinctrl-names` and `pinctrl-N`. The `N`-th name
+//! is the name of the state, and `pinctrl-N` is the list of the phandles of the configuration
+//! nodes, which are the descendants of the nodes of the pin controllers.
+//!
+//! Reference: <https://www.kernel.org/doc/Documentation/devicetree/bindings/pinctrl/pinctrl-bindings.txt>
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+
+use fdt::node::FdtNode;
+use log::{info, warn};
+use ostd::{
+    arch::boot::DEVICE_TREE,
+    io::MmioRegion,
+    sync::{LocalIrqDisabled, SpinLock},
+};
+
+use crate::GpioError;
+
+const PINCTRL_SINGLE_COMPATIBLES: &[&str] = &["pinctrl-single"];
+
+/// The registered pin controllers, with the phandles of their nodes.
+static PINCTRLS: SpinLock<Vec<(u32, Arc<dyn PinctrlDriver>)>, LocalIrqDisabled> =
+    SpinLock::new(Vec::new());
+
+/// A pin controller driver.
+pub trait PinctrlDriver: Send + Sync {
+    /// Returns the name of the controller.
+    fn name(&self) -> &str;
+
+    /// Applies the configuration node, which is a descendant of the node of the controller.
+    fn apply(&self, config: &FdtNode) -> Result<(), GpioError>;
+}
+
+/// Registers a pin controller with the phandle of its node in the device tree.
+pub fn register_pinctrl(phandle: u32, driver: Arc<dyn PinctrlDriver>) {
+    info!("[GPIO]: registered pin controller {}", driver.name());
+    PINCTRLS.lock().push((phandle, driver));
+}
+
+/// Applies the state of the device node with the name (e.g., `default`).
+///
+/// This function fails with [`GpioError::Busy`] if the pin controller is not registered yet.
+pub fn apply_pinctrl_state(node: &FdtNode, state: &str) -> Result<(), GpioError> {
+    let index = node
+        .property("pinctrl-names")
+        .map(|prop| prop.value)
+        .into_iter()
+        .flat_map(|value| value.split(|b| *b == 0))
+        .position(|name| name == state.as_bytes())
+        .ok_or(GpioError::InvalidArgs)?;
+    let device_tree = DEVICE_TREE.get().unwrap();
+    for phandle in read_cells(node, &format!("pinctrl-{}", index)) {
+        let config = device_tree
+            .find_phandle(phandle)
+            .ok_or(GpioError::InvalidArgs)?;
+        let driver = find_controller(phandle).ok_or(GpioError::Busy)?;
+        driver.apply(&config)?;
+    }
+    Ok(())
+}
+
+/// Returns the controller whose node is an ancestor of the configuration node.
+fn find_controller(config_phandle: u32) -> Option<Arc<dyn PinctrlDriver>> {
+    let device_tree = DEVICE_TREE.get().unwrap();
+    let controllers = PINCTRLS.lock().clone();
+    controllers.into_iter().find_map(|(phandle, driver)| {
+        let node = device_tree.find_phandle(phandle)?;
+        is_descendant(&node, config_phandle).then_some(driver)
+    })
+}
+
+fn is_descendant(node: &FdtNode, phandle: u32) -> bool {
+    node.children().any(|child| {
+        child
+            .property("phandle")
+            .and_then(|prop| prop.as_usize())
+            .is_some_and(|p| p as u32 == phandle)
+            || is_descendant(&child, phandle)
+    })
+}
+
+/// Probes the pin controllers, and applies the `default` states of the enabled nodes.
+pub(crate) fn init() {
+    let device_tree = DEVICE_TREE.get().unwrap();
+    for node in device_tree.all_nodes().filter(is_enabled) {
+        if !node.compatible().is_some_and(|compatible| {
+            compatible
+                .all()
+                .any(|c| PINCTRL_SINGLE_COMPATIBLES.contains(&c))
+        }) {
+            continue;
+        }
+        let Some(phandle) = node.property("phandle").and_then(|prop| prop.as_usize()) else {
+            continue;
+        };
+        match PinctrlSingle::new(&node) {
+            Ok(driver) => register_pinctrl(phandle as u32, Arc::new(driver)),
+            Err(err) => warn!("[GPIO]: {}: cannot probe the pins: {:?}", node.name, err),
+        }
+    }
+
+    for node in device_tree.all_nodes().filter(is_enabled) {
+        if node.property("pinctrl-0").is_none() {
+            continue;
+        }
+        if let Err(err) = apply_pinctrl_state(&node, "default") {
+            warn!(
+                "[GPIO]: {}: cannot apply the default pin state: {:?}",
+                node.name, err
+            );
+        }
+    }
+}
+
+fn is_enabled(node: &FdtNode) -> bool {
+    node.property("status")
+        .and_then(|prop| prop.as_str())
+        .is_none_or(|status| status == "okay" || status == "ok")
+}
+
+/// A pin controller whose pins are configured by the bits of registers.
+///
+/// The configuration nodes have `pinctrl-single,pins`, which are the pairs of the offsets of
+/// the registers and the values, and `pinctrl-single,bits`, which are the triples of the
+/// offsets, the values, and the masks. The values of `pinctrl-single,pins` are masked by
+/// `pinctrl-single,function-mask` of the controller.
+///
+/// Reference: <https://www.kernel.org/doc/Documentation/devicetree/bindings/pinctrl/pinctrl-single.txt>
+struct PinctrlSingle {
+    name: String,
+    io_mem: SpinLock<MmioRegion, LocalIrqDisabled>,
+    /// The width of the registers in bits
+    register_width: u32,
+    function_mask: u32,
+}
+
+impl PinctrlSingle {
+    fn new(node: &FdtNode) -> Result<Self, GpioError> {
+        let register_width = read_u32(node, "pinctrl-single,register-width").unwrap_or(32);
+        if ![8, 16, 32].contains(&register_width) {
+            return Err(GpioError::InvalidArgs);
+        }
+        let function_mask = read_u32(node, "pinctrl-single,function-mask").unwrap_or(u32::MAX);
+        let io_mem = MmioRegion::claim_fdt_reg(node).map_err(|_| GpioError::Busy)?;
+
+        Ok(Self {
+            name: node.name.to_string(),
+            io_mem: SpinLock::new(io_mem),
+            register_width,
+            function_mask,
+        })
+    }
+
+    fn update(&self, offset: usize, value: u32, mask: u32) -> Result<(), GpioError> {
+        let io_mem = self.io_mem.lock();
+        let update = |old: u32| (old & !mask) | (value & mask);
+        let result = match self.register_width {
+            8 => io_mem
+                .reg::<u8>(offset)
+                .map(|reg| reg.write(update(reg.read() as u32) as u8)),
+            16 => io_mem
+                .reg::<u16>(offset)
+                .map(|reg| reg.write(update(reg.read() as u32) as u16)),
+            _ => io_mem
+                .reg::<u32>(offset)
+                .map(|reg| reg.write(update(reg.read()))),
+        };
+        result.map_err(|_| GpioError::InvalidArgs)
+    }
+}
+
+impl PinctrlDriver for PinctrlSingle {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn apply(&self, config: &FdtNode) -> Result<(), GpioError> {
+        let pins = read_cells(config, "pinctrl-single,pins");
+        for pin in pins.chunks_exact(2) {
+            self.update(pin[0] as usize, pin[1], self.function_mask)?;
+        }
+        let bits = read_cells(config, "pinctrl-single,bits");
+        for bit in bits.chunks_exact(3) {
+            self.update(bit[0] as usize, bit[1], bit[2])?;
+        }
+        Ok(())
+    }
+}
+
+fn read_u32(node: &FdtNode, name: &str) -> Option<u32> {
+    read_cells(node, name).first().copied()
+}
+
+/// Reads the property as big-endian `u32` cells.
+fn read_cells(node: &FdtNode, name: &str) -> Vec<u32> {
+    node.property(name)
+        .map(|prop| {
+            prop.value
+                .chunks_exact(4)
+                .map(|bytes| u32::from_be_bytes(bytes.try_into().unwrap()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
@@ -0,0 +1,270 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The driver of the GPIO controller of the SiFive SoCs (e.g., FU540 and FU740).
+//!
+//! Each line has a bit in each register, and raises its own interrupt, which is the line's
+//! entry of `interrupts` in the device tree. The pending bits of the interrupts are cleared by
+//! writing ones.
+
+use alloc::{
+    string::{String, ToString},
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+
+use log::{info, warn};
+use ostd::{
+    arch::boot::DEVICE_TREE,
+    io::MmioRegion,
+    sync::{LocalIrqDisabled, SpinLock},
+    trap::IrqLine,
+};
+use spin::Once;
+
+use crate::{GpioChip, GpioDirection, GpioDriver, GpioError, GpioIrqTrigger};
+
+const SIFIVE_GPIO_COMPATIBLES: &[&str] = &["sifive,gpio0", "sifive,fu540-c000-gpio"];
+
+/// The maximum number of the lines.
+const MAX_NGPIO: u32 = 32;
+
+const SIFIVE_GPIO_INPUT_VAL: usize = 0x00;
+const SIFIVE_GPIO_INPUT_EN: usize = 0x04;
+const SIFIVE_GPIO_OUTPUT_EN: usize = 0x08;
+const SIFIVE_GPIO_OUTPUT_VAL: usize = 0x0c;
+const SIFIVE_GPIO_RISE_IE: usize = 0x18;
+const SIFIVE_GPIO_RISE_IP: usize = 0x1c;
+const SIFIVE_GPIO_FALL_IE: usize = 0x20;
+const SIFIVE_GPIO_FALL_IP: usize = 0x24;
+const SIFIVE_GPIO_HIGH_IE: usize = 0x28;
+const SIFIVE_GPIO_HIGH_IP: usize = 0x2c;
+const SIFIVE_GPIO_LOW_IE: usize = 0x30;
+const SIFIVE_GPIO_LOW_IP: usize = 0x34;
+
+const IE_REGS: [usize; 4] = [
+    SIFIVE_GPIO_RISE_IE,
+    SIFIVE_GPIO_FALL_IE,
+    SIFIVE_GPIO_HIGH_IE,
+    SIFIVE_GPIO_LOW_IE,
+];
+const IP_REGS: [usize; 4] = [
+    SIFIVE_GPIO_RISE_IP,
+    SIFIVE_GPIO_FALL_IP,
+    SIFIVE_GPIO_HIGH_IP,
+    SIFIVE_GPIO_LOW_IP,
+];
+
+struct SifiveGpio {
+    label: String,
+    ngpio: u32,
+    io_mem: SpinLock<MmioRegion, LocalIrqDisabled>,
+    /// The IRQ lines of the GPIO lines, which are kept alive with the controller.
+    irq_lines: Once<Vec<IrqLine>>,
+}
+
+/// Probes the controllers in the device tree, and registers them.
+pub(crate) fn init() {
+    for node in DEVICE_TREE.get().unwrap().all_nodes() {
+        if !node.compatible().is_some_and(|compatible| {
+            compatible
+                .all()
+                .any(|c| SIFIVE_GPIO_COMPATIBLES.contains(&c))
+        }) {
+            continue;
+        }
+        if node
+            .property("status")
+            .and_then(|prop| prop.as_str())
+            .is_some_and(|status| status != "okay" && status != "ok")
+        {
+            continue;
+        }
+
+        let irqs: Vec<usize> = node
+            .interrupts()
+            .map(|irqs| irqs.collect())
+            .unwrap_or_default();
+        let ngpio = node
+            .property("ngpios")
+            .and_then(|prop| prop.as_usize())
+            .map_or(irqs.len() as u32, |ngpio| ngpio as u32)
+            .min(MAX_NGPIO);
+        if ngpio == 0 {
+            warn!("[GPIO]: {}: unknown number of GPIOs", node.name);
+            continue;
+        }
+        let io_mem = match MmioRegion::claim_fdt_reg(&node) {
+            Ok(io_mem) => io_mem,
+            Err(err) => {
+                warn!(
+                    "[GPIO]: {}: cannot claim the registers: {:?}",
+                    node.name, err
+                );
+                continue;
+            }
+        };
+
+        let driver = Arc::new(SifiveGpio {
+            label: node.name.to_string(),
+            ngpio,
+            io_mem: SpinLock::new(io_mem),
+            irq_lines: Once::new(),
+        });
+        driver.reset();
+
+        info!("[GPIO]: found SiFive GPIO controller {}", node.name);
+        let phandle = node
+            .property("phandle")
+            .and_then(|prop| prop.as_usize())
+            .map(|phandle| phandle as u32);
+        let chip = crate::register_chip(driver.clone(), phandle);
+        driver.init_irqs(&irqs, Arc::downgrade(&chip));
+    }
+}
+
+impl SifiveGpio {
+    /// Disables and clears all the interrupts.
+    fn reset(&self) {
+        let io_mem = self.io_mem.lock();
+        for reg in IE_REGS {
+            write(&io_mem, reg, 0);
+        }
+        for reg in IP_REGS {
+            write(&io_mem, reg, u32::MAX);
+        }
+    }
+
+    fn init_irqs(self: &Arc<Self>, irqs: &[usize], chip: Weak<GpioChip>) {
+        let mut irq_lines = Vec::new();
+        for (offset, irq) in irqs.iter().take(self.ngpio as usize).enumerate() {
+            let Some(mut irq_line) = u8::try_from(*irq)
+                .ok()
+                .and_then(|irq| IrqLine::alloc_specific(irq).ok())
+            else {
+                warn!("[GPIO]: {}: IRQ {} is not available", self.label, irq);
+                continue;
+            };
+            let (driver, chip) = (Arc::downgrade(self), chip.clone());
+            irq_line.on_active(move |_| {
+                let (Some(driver), Some(chip)) = (driver.upgrade(), chip.upgrade()) else {
+                    return;
+                };
+                if driver.ack_irq(offset as u32) {
+                    chip.handle_irq(offset as u32);
+                }
+            });
+            irq_lines.push(irq_line);
+        }
+        self.irq_lines.call_once(|| irq_lines);
+    }
+
+    fn update(&self, reg: usize, offset: u32, value: bool) {
+        let io_mem = self.io_mem.lock();
+        let old = read(&io_mem, reg);
+        let new = if value {
+            old | (1 << offset)
+        } else {
+            old & !(1 << offset)
+        };
+        write(&io_mem, reg, new);
+    }
+
+    /// Clears the pending interrupts of the line, and returns whether there were any.
+    fn ack_irq(&self, offset: u32) -> bool {
+        let io_mem = self.io_mem.lock();
+        let mut is_pending = false;
+        for reg in IP_REGS {
+            if read(&io_mem, reg) & (1 << offset) != 0 {
+                write(&io_mem, reg, 1 << offset);
+                is_pending = true;
+            }
+        }
+        is_pending
+    }
+}
+
+impl GpioDriver for SifiveGpio {
+    fn label(&self) -> &str {
+        &self.label
+    }
+
+    fn ngpio(&self) -> u32 {
+        self.ngpio
+    }
+
+    fn direction(&self, offset: u32) -> GpioDirection {
+        if read(&self.io_mem.lock(), SIFIVE_GPIO_OUTPUT_EN) & (1 << offset) != 0 {
+            GpioDirection::Output
+        } else {
+            GpioDirection::Input
+        }
+    }
+
+    fn set_input(&self, offset: u32) -> Result<(), GpioError> {
+        self.update(SIFIVE_GPIO_OUTPUT_EN, offset, false);
+        self.update(SIFIVE_GPIO_INPUT_EN, offset, true);
+        Ok(())
+    }
+
+    fn set_output(&self, offset: u32, value: bool) -> Result<(), GpioError> {
+        // Set the value first, so that the line does not glitch.
+        self.update(SIFIVE_GPIO_OUTPUT_VAL, offset, value);
+        self.update(SIFIVE_GPIO_INPUT_EN, offset, false);
+        self.update(SIFIVE_GPIO_OUTPUT_EN, offset, true);
+        Ok(())
+    }
+
+    fn get_value(&self, offset: u32) -> bool {
+        let io_mem = self.io_mem.lock();
+        // The input of an output line is disabled, so its value is read from the output.
+        let reg = if read(&io_mem, SIFIVE_GPIO_OUTPUT_EN) & (1 << offset) != 0 {
+            SIFIVE_GPIO_OUTPUT_VAL
+        } else {
+            SIFIVE_GPIO_INPUT_VAL
+        };
+        read(&io_mem, reg) & (1 << offset) != 0
+    }
+
+    fn set_value(&self, offset: u32, value: bool) {
+        self.update(SIFIVE_GPIO_OUTPUT_VAL, offset, value);
+    }
+
+    fn set_irq_trigger(
+        &self,
+        offset: u32,
+        trigger: Option<GpioIrqTrigger>,
+    ) -> Result<(), GpioError> {
+        let has_irq_line = self
+            .irq_lines
+            .get()
+            .is_some_and(|lines| (offset as usize) < lines.len());
+        if trigger.is_some() && !has_irq_line {
+            return Err(GpioError::NotSupported);
+        }
+
+        let enabled = match trigger {
+            None => [false; 4],
+            Some(GpioIrqTrigger::RisingEdge) => [true, false, false, false],
+            Some(GpioIrqTrigger::FallingEdge) => [false, true, false, false],
+            Some(GpioIrqTrigger::BothEdges) => [true, true, false, false],
+            Some(GpioIrqTrigger::HighLevel) => [false, false, true, false],
+            Some(GpioIrqTrigger::LowLevel) => [false, false, false, true],
+        };
+        // Clear the stale pending bits before the interrupts are enabled.
+        self.ack_irq(offset);
+        for (reg, is_enabled) in IE_REGS.into_iter().zip(enabled) {
+            self.update(reg, offset, is_enabled);
+        }
+        Ok(())
+    }
+}
+
+fn read(io_mem: &MmioRegion, reg: usize) -> u32 {
+    io_mem.reg::<u32>(reg).map_or(0, |reg| reg.read())
+}
+
+fn write(io_mem: &MmioRegion, reg: usize, value: u32) {
+    if let Ok(reg) = io_mem.reg::<u32>(reg) {
+        reg.write(value);
+    }
+}
@@ -0,0 +1,185 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The registered I2C adapters, and the SMBus protocols emulated on them.
+
+use alloc::{sync::Arc, vec::Vec};
+
+use ostd::sync::Mutex;
+
+use crate::{I2cAdapterDriver, I2cError, I2cMsg};
+
+/// The maximum number of the bytes of an SMBus block.
+pub const I2C_SMBUS_BLOCK_MAX: usize = 32;
+
+/// A registered I2C adapter.
+///
+/// The transfers may sleep, so they must not be started in the atomic context.
+pub struct I2cAdapter {
+    index: usize,
+    driver: Arc<dyn I2cAdapterDriver>,
+    /// The lock of the bus, which serializes the transfers
+    bus_lock: Mutex<()>,
+}
+
+impl I2cAdapter {
+    pub(crate) fn new(index: usize, driver: Arc<dyn I2cAdapterDriver>) -> Self {
+        Self {
+            index,
+            driver,
+            bus_lock: Mutex::new(()),
+        }
+    }
+
+    /// Returns the index of the adapter.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the name of the adapter.
+    pub fn name(&self) -> &str {
+        self.driver.name()
+    }
+
+    /// Returns whether the adapter supports the SMBus quick command.
+    pub fn supports_quick(&self) -> bool {
+        self.driver.supports_empty_msgs()
+    }
+
+    /// Transfers the messages, which are separated by repeated START conditions.
+    pub fn transfer(&self, msgs: &mut [I2cMsg]) -> Result<(), I2cError> {
+        if msgs.is_empty() || msgs.iter().any(|msg| msg.addr() > 0x7f) {
+            return Err(I2cError::InvalidArgs);
+        }
+        if !self.driver.supports_empty_msgs() && msgs.iter().any(|msg| msg.is_empty()) {
+            return Err(I2cError::NotSupported);
+        }
+
+        let _guard = self.bus_lock.lock();
+        self.driver.transfer(msgs)
+    }
+
+    /// Sends the SMBus quick command, i.e., the address with the read/write bit.
+    pub fn smbus_quick(&self, addr: u16, is_read: bool) -> Result<(), I2cError> {
+        let msg = if is_read {
+            I2cMsg::Read { addr, buf: &mut [] }
+        } else {
+            I2cMsg::Write { addr, buf: &[] }
+        };
+        self.transfer(&mut [msg])
+    }
+
+    /// Reads a byte without a command.
+    pub fn smbus_read_byte(&self, addr: u16) -> Result<u8, I2cError> {
+        let mut buf = [0u8; 1];
+        self.transfer(&mut [I2cMsg::Read {
+            addr,
+            buf: &mut buf,
+        }])?;
+        Ok(buf[0])
+    }
+
+    /// Writes a byte without a command.
+    pub fn smbus_write_byte(&self, addr: u16, value: u8) -> Result<(), I2cError> {
+        self.transfer(&mut [I2cMsg::Write {
+            addr,
+            buf: &[value],
+        }])
+    }
+
+    /// Reads a byte after the command (e.g., the register).
+    pub fn smbus_read_byte_data(&self, addr: u16, command: u8) -> Result<u8, I2cError> {
+        let mut buf = [0u8; 1];
+        self.write_read(addr, &[command], &mut buf)?;
+        Ok(buf[0])
+    }
+
+    /// Writes a byte after the command.
+    pub fn smbus_write_byte_data(&self, addr: u16, command: u8, value: u8) -> Result<(), I2cError> {
+        self.transfer(&mut [I2cMsg::Write {
+            addr,
+            buf: &[command, value],
+        }])
+    }
+
+    /// Reads a little-endian word after the command.
+    pub fn smbus_read_word_data(&self, addr: u16, command: u8) -> Result<u16, I2cError> {
+        let mut buf = [0u8; 2];
+        self.write_read(addr, &[command], &mut buf)?;
+        Ok(u16::from_le_bytes(buf))
+    }
+
+    /// Writes a little-endian word after the command.
+    pub fn smbus_write_word_data(
+        &self,
+        addr: u16,
+        command: u8,
+        value: u16,
+    ) -> Result<(), I2cError> {
+        let [low, high] = value.to_le_bytes();
+        self.transfer(&mut [I2cMsg::Write {
+            addr,
+            buf: &[command, low, high],
+        }])
+    }
+
+    /// Writes a word after the command, and reads a word back.
+    pub fn smbus_process_call(&self, addr: u16, command: u8, value: u16) -> Result<u16, I2cError> {
+        let [low, high] = value.to_le_bytes();
+        let mut buf = [0u8; 2];
+        self.write_read(addr, &[command, low, high], &mut buf)?;
+        Ok(u16::from_le_bytes(buf))
+    }
+
+    /// Writes a block, which is prefixed by its length, after the command.
+    pub fn smbus_write_block_data(
+        &self,
+        addr: u16,
+        command: u8,
+        data: &[u8],
+    ) -> Result<(), I2cError> {
+        if data.len() > I2C_SMBUS_BLOCK_MAX {
+            return Err(I2cError::InvalidArgs);
+        }
+        let mut buf = Vec::with_capacity(data.len() + 2);
+        buf.extend_from_slice(&[command, data.len() as u8]);
+        buf.extend_from_slice(data);
+        self.transfer(&mut [I2cMsg::Write { addr, buf: &buf }])
+    }
+
+    /// Reads the bytes after the command, without the length prefix of an SMBus block.
+    pub fn smbus_read_i2c_block_data(
+        &self,
+        addr: u16,
+        command: u8,
+        buf: &mut [u8],
+    ) -> Result<(), I2cError> {
+        if buf.len() > I2C_SMBUS_BLOCK_MAX {
+            return Err(I2cError::InvalidArgs);
+        }
+        self.write_read(addr, &[command], buf)
+    }
+
+    /// Writes the bytes after the command, without the length prefix of an SMBus block.
+    pub fn smbus_write_i2c_block_data(
+        &self,
+        addr: u16,
+        command: u8,
+        data: &[u8],
+    ) -> Result<(), I2cError> {
+        if data.len() > I2C_SMBUS_BLOCK_MAX {
+            return Err(I2cError::InvalidArgs);
+        }
+        let mut buf = Vec::with_capacity(data.len() + 1);
+        buf.push(command);
+        buf.extend_from_slice(data);
+        self.transfer(&mut [I2cMsg::Write { addr, buf: &buf }])
+    }
+
+    /// Writes the bytes, and reads the bytes after a repeated START condition.
+    pub fn write_read(&self, addr: u16, write: &[u8], read: &mut [u8]) -> Result<(), I2cError> {
+        self.transfer(&mut [
+            I2cMsg::Write { addr, buf: write },
+            I2cMsg::Read { addr, buf: read },
+        ])
+    }
+}
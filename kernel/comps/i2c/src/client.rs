@@ -0,0 +1,157 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The I2C client devices on the adapters.
+
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+
+use crate::{I2cAdapter, I2cError, I2cMsg};
+
+/// An I2C device on an adapter.
+///
+/// The methods transfer the messages to the address of the device. See [`I2cAdapter`] for
+/// the SMBus protocols.
+pub struct I2cClient {
+    adapter: Arc<I2cAdapter>,
+    addr: u16,
+    name: String,
+    compatibles: Vec<String>,
+    #[cfg(target_arch = "riscv64")]
+    node: fdt::node::FdtNode<'static, 'static>,
+}
+
+impl I2cClient {
+    /// Returns the adapter of the device.
+    pub fn adapter(&self) -> &Arc<I2cAdapter> {
+        &self.adapter
+    }
+
+    /// Returns the address of the device.
+    pub fn addr(&self) -> u16 {
+        self.addr
+    }
+
+    /// Returns the name of the device, which is its first compatible string without the
+    /// vendor, like Linux.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns whether the device is compatible with any of the compatible strings.
+    pub fn is_compatible_with(&self, compatibles: &[&str]) -> bool {
+        self.compatibles
+            .iter()
+            .any(|compatible| compatibles.contains(&compatible.as_str()))
+    }
+
+    /// Returns the node of the device in the device tree.
+    #[cfg(target_arch = "riscv64")]
+    pub fn fdt_node(&self) -> &fdt::node::FdtNode<'static, 'static> {
+        &self.node
+    }
+
+    /// Reads the bytes from the device.
+    pub fn read(&self, buf: &mut [u8]) -> Result<(), I2cError> {
+        self.adapter.transfer(&mut [I2cMsg::Read {
+            addr: self.addr,
+            buf,
+        }])
+    }
+
+    /// Writes the bytes to the device.
+    pub fn write(&self, buf: &[u8]) -> Result<(), I2cError> {
+        self.adapter.transfer(&mut [I2cMsg::Write {
+            addr: self.addr,
+            buf,
+        }])
+    }
+
+    /// Writes the bytes, and reads the bytes after a repeated START condition.
+    pub fn write_read(&self, write: &[u8], read: &mut [u8]) -> Result<(), I2cError> {
+        self.adapter.write_read(self.addr, write, read)
+    }
+
+    /// Reads the register of a byte.
+    pub fn read_byte_data(&self, register: u8) -> Result<u8, I2cError> {
+        self.adapter.smbus_read_byte_data(self.addr, register)
+    }
+
+    /// Writes the register of a byte.
+    pub fn write_byte_data(&self, register: u8, value: u8) -> Result<(), I2cError> {
+        self.adapter
+            .smbus_write_byte_data(self.addr, register, value)
+    }
+
+    /// Reads the register of a little-endian word.
+    pub fn read_word_data(&self, register: u8) -> Result<u16, I2cError> {
+        self.adapter.smbus_read_word_data(self.addr, register)
+    }
+
+    /// Writes the register of a little-endian word.
+    pub fn write_word_data(&self, register: u8, value: u16) -> Result<(), I2cError> {
+        self.adapter
+            .smbus_write_word_data(self.addr, register, value)
+    }
+
+    /// Reads the consecutive registers from the first register.
+    pub fn read_block_data(&self, register: u8, buf: &mut [u8]) -> Result<(), I2cError> {
+        self.adapter
+            .smbus_read_i2c_block_data(self.addr, register, buf)
+    }
+
+    /// Writes the consecutive registers from the first register.
+    pub fn write_block_data(&self, register: u8, data: &[u8]) -> Result<(), I2cError> {
+        self.adapter
+            .smbus_write_i2c_block_data(self.addr, register, data)
+    }
+}
+
+/// Returns the clients that are described by the enabled children of the adapter's node.
+#[cfg(target_arch = "riscv64")]
+pub(crate) fn probe_clients(
+    adapter: &Arc<I2cAdapter>,
+    node: &fdt::node::FdtNode<'static, 'static>,
+) -> Vec<I2cClient> {
+    node.children()
+        .filter(|child| {
+            child
+                .property("status")
+                .and_then(|prop| prop.as_str())
+                .is_none_or(|status| status == "okay" || status == "ok")
+        })
+        .filter_map(|child| {
+            let reg = child.property("reg")?.value.get(0..4)?;
+            let addr = u32::from_be_bytes(reg.try_into().unwrap());
+            // The 10-bit addresses are not supported.
+            if addr > 0x7f {
+                log::warn!("[I2C]: {}: unsupported address {:#x}", child.name, addr);
+                return None;
+            }
+
+            let compatibles: Vec<String> = child
+                .compatible()?
+                .all()
+                .map(|compatible| compatible.to_string())
+                .collect();
+            let name = compatibles
+                .first()
+                .map(|compatible| {
+                    compatible
+                        .split_once(',')
+                        .map_or(compatible.as_str(), |(_, name)| name)
+                })?
+                .to_string();
+
+            Some(I2cClient {
+                adapter: adapter.clone(),
+                addr: addr as u16,
+                name,
+                compatibles,
+                node: child,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,232 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The driver of the Synopsys DesignWare I2C controller.
+//!
+//! The controller is used as the master, whose commands (i.e., the bytes to write and the
+//! reads) are pushed into the transmit FIFO. The target address cannot be changed when the
+//! controller is enabled, so the messages of a transfer must have the same address.
+//!
+//! The clock controllers are not supported, so the SCL timings are left as set by the
+//! firmware (or the reset defaults of the controller).
+
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+};
+
+use log::{info, warn};
+use ostd::{arch::boot::DEVICE_TREE, io::MmioRegion};
+
+use crate::{poll_until, I2cAdapterDriver, I2cError, I2cMsg};
+
+const DW_I2C_COMPATIBLES: &[&str] = &["snps,designware-i2c"];
+
+const IC_CON: usize = 0x00;
+const IC_TAR: usize = 0x04;
+const IC_DATA_CMD: usize = 0x10;
+const IC_INTR_MASK: usize = 0x30;
+const IC_RAW_INTR_STAT: usize = 0x34;
+const IC_RX_TL: usize = 0x38;
+const IC_TX_TL: usize = 0x3c;
+const IC_CLR_INTR: usize = 0x40;
+const IC_CLR_TX_ABRT: usize = 0x54;
+const IC_CLR_STOP_DET: usize = 0x60;
+const IC_ENABLE: usize = 0x6c;
+const IC_STATUS: usize = 0x70;
+const IC_TX_ABRT_SOURCE: usize = 0x80;
+const IC_ENABLE_STATUS: usize = 0x9c;
+
+const IC_CON_MASTER: u32 = 1 << 0;
+const IC_CON_SPEED_STD: u32 = 1 << 1;
+const IC_CON_SPEED_FAST: u32 = 2 << 1;
+const IC_CON_RESTART_EN: u32 = 1 << 5;
+const IC_CON_SLAVE_DISABLE: u32 = 1 << 6;
+
+const IC_DATA_CMD_READ: u32 = 1 << 8;
+const IC_DATA_CMD_STOP: u32 = 1 << 9;
+const IC_DATA_CMD_RESTART: u32 = 1 << 10;
+
+const IC_STATUS_ACTIVITY: u32 = 1 << 0;
+const IC_STATUS_TFNF: u32 = 1 << 1;
+const IC_STATUS_RFNE: u32 = 1 << 3;
+
+const IC_INTR_TX_ABRT: u32 = 1 << 6;
+const IC_INTR_STOP_DET: u32 = 1 << 9;
+
+/// The sources of aborts that mean the address or the data is not acknowledged.
+const ABRT_NOACK: u32 = 0xf;
+const ABRT_ARB_LOST: u32 = 1 << 12;
+
+/// The bus frequency (in Hz) above which the fast mode is used.
+const STANDARD_MODE_MAX_HZ: usize = 100_000;
+
+struct DwI2c {
+    name: String,
+    io_mem: MmioRegion,
+}
+
+/// Probes the controllers in the device tree, and registers them.
+pub(crate) fn init() {
+    for node in DEVICE_TREE.get().unwrap().all_nodes() {
+        if !node
+            .compatible()
+            .is_some_and(|compatible| compatible.all().any(|c| DW_I2C_COMPATIBLES.contains(&c)))
+        {
+            continue;
+        }
+        if node
+            .property("status")
+            .and_then(|prop| prop.as_str())
+            .is_some_and(|status| status != "okay" && status != "ok")
+        {
+            continue;
+        }
+
+        let io_mem = match MmioRegion::claim_fdt_reg(&node) {
+            Ok(io_mem) => io_mem,
+            Err(err) => {
+                warn!(
+                    "[I2C]: {}: cannot claim the registers: {:?}",
+                    node.name, err
+                );
+                continue;
+            }
+        };
+        let bus_hz = node
+            .property("clock-frequency")
+            .and_then(|prop| prop.as_usize())
+            .unwrap_or(STANDARD_MODE_MAX_HZ);
+        let driver = DwI2c {
+            name: node.name.to_string(),
+            io_mem,
+        };
+        if driver.reset(bus_hz).is_err() {
+            warn!("[I2C]: {}: cannot disable the controller", node.name);
+            continue;
+        }
+
+        info!("[I2C]: found DesignWare I2C controller {}", node.name);
+        crate::register_fdt_adapter(Arc::new(driver), &node);
+    }
+}
+
+impl DwI2c {
+    /// Configures the controller as the master, which is disabled.
+    fn reset(&self, bus_hz: usize) -> Result<(), I2cError> {
+        self.disable()?;
+        let speed = if bus_hz > STANDARD_MODE_MAX_HZ {
+            IC_CON_SPEED_FAST
+        } else {
+            IC_CON_SPEED_STD
+        };
+        self.write(
+            IC_CON,
+            IC_CON_MASTER | IC_CON_SLAVE_DISABLE | IC_CON_RESTART_EN | speed,
+        );
+        // The interrupts are polled in the raw status.
+        self.write(IC_INTR_MASK, 0);
+        self.write(IC_RX_TL, 0);
+        self.write(IC_TX_TL, 0);
+        Ok(())
+    }
+
+    fn disable(&self) -> Result<(), I2cError> {
+        self.write(IC_ENABLE, 0);
+        poll_until(|| self.read(IC_ENABLE_STATUS) & 1 == 0)
+    }
+
+    /// Waits for the condition, and fails if the transfer is aborted.
+    fn wait(&self, mut cond: impl FnMut() -> bool) -> Result<(), I2cError> {
+        let mut result = Ok(());
+        poll_until(|| {
+            if self.read(IC_RAW_INTR_STAT) & IC_INTR_TX_ABRT != 0 {
+                result = Err(self.abort_error());
+                return true;
+            }
+            cond()
+        })?;
+        result
+    }
+
+    /// Returns the error of the aborted transfer, and clears the abort.
+    fn abort_error(&self) -> I2cError {
+        let source = self.read(IC_TX_ABRT_SOURCE);
+        let _ = self.read(IC_CLR_TX_ABRT);
+        if source & ABRT_NOACK != 0 {
+            I2cError::Nack
+        } else if source & ABRT_ARB_LOST != 0 {
+            I2cError::ArbitrationLost
+        } else {
+            I2cError::IoError
+        }
+    }
+
+    fn do_transfer(&self, msgs: &mut [I2cMsg]) -> Result<(), I2cError> {
+        let nr_msgs = msgs.len();
+        for (index, msg) in msgs.iter_mut().enumerate() {
+            let len = msg.len();
+            for byte_index in 0..len {
+                let mut cmd = 0;
+                if index > 0 && byte_index == 0 {
+                    cmd |= IC_DATA_CMD_RESTART;
+                }
+                if index == nr_msgs - 1 && byte_index == len - 1 {
+                    cmd |= IC_DATA_CMD_STOP;
+                }
+
+                self.wait(|| self.read(IC_STATUS) & IC_STATUS_TFNF != 0)?;
+                match msg {
+                    I2cMsg::Write { buf, .. } => {
+                        self.write(IC_DATA_CMD, cmd | buf[byte_index] as u32)
+                    }
+                    I2cMsg::Read { buf, .. } => {
+                        self.write(IC_DATA_CMD, cmd | IC_DATA_CMD_READ);
+                        self.wait(|| self.read(IC_STATUS) & IC_STATUS_RFNE != 0)?;
+                        buf[byte_index] = self.read(IC_DATA_CMD) as u8;
+                    }
+                }
+            }
+        }
+
+        self.wait(|| self.read(IC_RAW_INTR_STAT) & IC_INTR_STOP_DET != 0)?;
+        let _ = self.read(IC_CLR_STOP_DET);
+        Ok(())
+    }
+
+    fn read(&self, offset: usize) -> u32 {
+        self.io_mem.reg::<u32>(offset).map_or(0, |reg| reg.read())
+    }
+
+    fn write(&self, offset: usize, value: u32) {
+        if let Ok(reg) = self.io_mem.reg::<u32>(offset) {
+            reg.write(value);
+        }
+    }
+}
+
+impl I2cAdapterDriver for DwI2c {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn transfer(&self, msgs: &mut [I2cMsg]) -> Result<(), I2cError> {
+        let addr = msgs[0].addr();
+        if msgs.iter().any(|msg| msg.addr() != addr) {
+            return Err(I2cError::NotSupported);
+        }
+
+        poll_until(|| self.read(IC_STATUS) & IC_STATUS_ACTIVITY == 0)?;
+        self.disable()?;
+        self.write(IC_TAR, addr as u32);
+        let _ = self.read(IC_CLR_INTR);
+        self.write(IC_ENABLE, 1);
+
+        let result = self.do_transfer(msgs);
+        if result.is_err() {
+            // Flush the FIFOs, and clear the stale status.
+            let _ = self.disable();
+            let _ = self.read(IC_CLR_INTR);
+        }
+        result
+    }
+}
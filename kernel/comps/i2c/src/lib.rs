@@ -0,0 +1,217 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The I2C subsystem of Asterinas.
+//!
+//! Each I2C controller is driven by an [`I2cAdapterDriver`] and registered as an
+//! [`I2cAdapter`], which serializes the transfers on the bus and emulates the SMBus
+//! protocols with plain I2C messages. The devices on the bus are described by the children
+//! of the node of the controller in the device tree, which become the [`I2cClient`]s of the
+//! adapter. The drivers of the devices (e.g., RTCs, PMICs, and sensors) find their clients
+//! by [`find_clients`]. The kernel exposes the adapters as `/dev/i2c-N`.
+//!
+//! The following drivers are provided:
+//!
+//! - `designware`: the Synopsys DesignWare I2C controller, which is used by many RISC-V SoCs
+//!   (e.g., the StarFive JH7110).
+//! - `ocores`: the OpenCores I2C controller, which is used by the SiFive SoCs.
+//!
+//! Both drivers poll the controllers, and are discovered through the device tree.
+
+#![no_std]
+#![deny(unsafe_code)]
+
+extern crate alloc;
+
+mod adapter;
+mod client;
+#[cfg(target_arch = "riscv64")]
+mod designware;
+#[cfg(target_arch = "riscv64")]
+mod ocores;
+
+use alloc::{sync::Arc, vec::Vec};
+
+use component::{init_component, ComponentInitError};
+use log::info;
+use ostd::sync::{LocalIrqDisabled, SpinLock};
+
+pub use self::{
+    adapter::{I2cAdapter, I2C_SMBUS_BLOCK_MAX},
+    client::I2cClient,
+};
+
+static ADAPTERS: SpinLock<Vec<Arc<I2cAdapter>>, LocalIrqDisabled> = SpinLock::new(Vec::new());
+static CLIENTS: SpinLock<Vec<Arc<I2cClient>>, LocalIrqDisabled> = SpinLock::new(Vec::new());
+
+#[init_component]
+fn i2c_component_init() -> Result<(), ComponentInitError> {
+    #[cfg(target_arch = "riscv64")]
+    {
+        designware::init();
+        ocores::init();
+    }
+    Ok(())
+}
+
+/// A message of an I2C transfer.
+///
+/// The addresses are 7-bit.
+#[derive(Debug)]
+pub enum I2cMsg<'a> {
+    /// Reads the bytes from the device.
+    Read {
+        /// The address of the device
+        addr: u16,
+        /// The buffer to read into
+        buf: &'a mut [u8],
+    },
+    /// Writes the bytes to the device.
+    Write {
+        /// The address of the device
+        addr: u16,
+        /// The bytes to write
+        buf: &'a [u8],
+    },
+}
+
+impl I2cMsg<'_> {
+    /// Returns the address of the device.
+    pub fn addr(&self) -> u16 {
+        match self {
+            I2cMsg::Read { addr, .. } | I2cMsg::Write { addr, .. } => *addr,
+        }
+    }
+
+    /// Returns the length of the message.
+    pub fn len(&self) -> usize {
+        match self {
+            I2cMsg::Read { buf, .. } => buf.len(),
+            I2cMsg::Write { buf, .. } => buf.len(),
+        }
+    }
+
+    /// Returns whether the message is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// The errors of I2C transfers.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum I2cError {
+    /// The messages are invalid.
+    InvalidArgs,
+    /// The device does not acknowledge the address or the data.
+    Nack,
+    /// Another master takes the bus.
+    ArbitrationLost,
+    /// The bus does not respond in time.
+    Timeout,
+    /// The messages are not supported by the controller.
+    NotSupported,
+    /// The controller reports another error.
+    IoError,
+}
+
+/// An I2C controller driver.
+///
+/// The messages are sent as a single transfer, i.e., they are separated by repeated START
+/// conditions, and the transfer ends with a STOP condition. The transfers are serialized by
+/// the adapter.
+pub trait I2cAdapterDriver: Send + Sync {
+    /// Returns the name of the controller.
+    fn name(&self) -> &str;
+
+    /// Transfers the messages.
+    fn transfer(&self, msgs: &mut [I2cMsg]) -> Result<(), I2cError>;
+
+    /// Returns whether the controller can send the messages without data (e.g., the SMBus
+    /// quick command).
+    fn supports_empty_msgs(&self) -> bool {
+        false
+    }
+}
+
+/// Registers an I2C controller driver, returning the registered adapter.
+pub fn register_adapter(driver: Arc<dyn I2cAdapterDriver>) -> Arc<I2cAdapter> {
+    let mut adapters = ADAPTERS.lock();
+    let adapter = Arc::new(I2cAdapter::new(adapters.len(), driver));
+    info!(
+        "[I2C]: registered i2c-{} ({})",
+        adapter.index(),
+        adapter.name()
+    );
+    adapters.push(adapter.clone());
+    adapter
+}
+
+/// Registers an I2C controller driver with the node of the controller in the device tree,
+/// returning the registered adapter.
+///
+/// The children of the node are registered as the clients of the adapter.
+#[cfg(target_arch = "riscv64")]
+pub fn register_fdt_adapter(
+    driver: Arc<dyn I2cAdapterDriver>,
+    node: &fdt::node::FdtNode<'static, 'static>,
+) -> Arc<I2cAdapter> {
+    let adapter = register_adapter(driver);
+    for client in client::probe_clients(&adapter, node) {
+        info!(
+            "[I2C]: found client {} at {:#x} on i2c-{}",
+            client.name(),
+            client.addr(),
+            adapter.index()
+        );
+        CLIENTS.lock().push(Arc::new(client));
+    }
+    adapter
+}
+
+/// Returns the adapter with the index.
+pub fn get_adapter(index: usize) -> Option<Arc<I2cAdapter>> {
+    ADAPTERS.lock().get(index).cloned()
+}
+
+/// Returns all the registered adapters.
+pub fn all_adapters() -> Vec<Arc<I2cAdapter>> {
+    ADAPTERS.lock().clone()
+}
+
+/// Returns all the clients on the adapters.
+pub fn all_clients() -> Vec<Arc<I2cClient>> {
+    CLIENTS.lock().clone()
+}
+
+/// Returns the clients that are compatible with any of the compatible strings.
+pub fn find_clients(compatibles: &[&str]) -> Vec<Arc<I2cClient>> {
+    CLIENTS
+        .lock()
+        .iter()
+        .filter(|client| client.is_compatible_with(compatibles))
+        .cloned()
+        .collect()
+}
+
+/// Polls `cond` until it returns `true`, or until the transfer times out.
+#[cfg(target_arch = "riscv64")]
+fn poll_until(mut cond: impl FnMut() -> bool) -> Result<(), I2cError> {
+    /// The time (in microseconds) to wait for a byte on the bus.
+    const XFER_TIMEOUT_US: u64 = 100_000;
+
+    let freq = ostd::arch::tsc_freq().max(1);
+    let deadline = ostd::arch::read_tsc() + XFER_TIMEOUT_US * freq / 1_000_000;
+    loop {
+        if cond() {
+            return Ok(());
+        }
+        if ostd::arch::read_tsc() > deadline {
+            // Check once more in case that we were preempted for a long time.
+            return if cond() {
+                Ok(())
+            } else {
+                Err(I2cError::Timeout)
+            };
+        }
+        core::hint::spin_loop();
+    }
+}
@@ -0,0 +1,233 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The driver of the OpenCores I2C controller, which is used by the SiFive SoCs.
+//!
+//! Each byte (including the address) is sent by a command, and its completion is polled.
+//! The registers are spaced by `reg-shift`, and accessed with `reg-io-width`.
+//!
+//! Reference: <https://opencores.org/projects/i2c>
+
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+};
+
+use log::{info, warn};
+use ostd::{arch::boot::DEVICE_TREE, io::MmioRegion};
+
+use crate::{poll_until, I2cAdapterDriver, I2cError, I2cMsg};
+
+const OCORES_COMPATIBLES: &[&str] = &[
+    "opencores,i2c-ocores",
+    "sifive,fu540-c000-i2c",
+    "sifive,i2c0",
+];
+
+const OCI2C_PRELOW: usize = 0;
+const OCI2C_PREHIGH: usize = 1;
+const OCI2C_CONTROL: usize = 2;
+const OCI2C_DATA: usize = 3;
+/// The command register when written, or the status register when read
+const OCI2C_CMD: usize = 4;
+const OCI2C_STATUS: usize = 4;
+
+const OCI2C_CTRL_EN: u8 = 0x80;
+
+const OCI2C_CMD_START: u8 = 0x80;
+const OCI2C_CMD_STOP: u8 = 0x40;
+const OCI2C_CMD_READ: u8 = 0x20;
+const OCI2C_CMD_WRITE: u8 = 0x10;
+const OCI2C_CMD_READ_NACK: u8 = 0x08;
+const OCI2C_CMD_IACK: u8 = 0x01;
+
+const OCI2C_STAT_NACK: u8 = 0x80;
+const OCI2C_STAT_BUSY: u8 = 0x40;
+const OCI2C_STAT_ARBLOST: u8 = 0x20;
+const OCI2C_STAT_TIP: u8 = 0x02;
+
+/// The default bus frequency (in Hz).
+const DEFAULT_BUS_HZ: usize = 100_000;
+
+struct Ocores {
+    name: String,
+    io_mem: MmioRegion,
+    reg_shift: u32,
+    reg_io_width: u32,
+}
+
+/// Probes the controllers in the device tree, and registers them.
+pub(crate) fn init() {
+    for node in DEVICE_TREE.get().unwrap().all_nodes() {
+        if !node
+            .compatible()
+            .is_some_and(|compatible| compatible.all().any(|c| OCORES_COMPATIBLES.contains(&c)))
+        {
+            continue;
+        }
+        if node
+            .property("status")
+            .and_then(|prop| prop.as_str())
+            .is_some_and(|status| status != "okay" && status != "ok")
+        {
+            continue;
+        }
+
+        let io_mem = match MmioRegion::claim_fdt_reg(&node) {
+            Ok(io_mem) => io_mem,
+            Err(err) => {
+                warn!(
+                    "[I2C]: {}: cannot claim the registers: {:?}",
+                    node.name, err
+                );
+                continue;
+            }
+        };
+        let read_usize = |name: &str| node.property(name).and_then(|prop| prop.as_usize());
+        let driver = Ocores {
+            name: node.name.to_string(),
+            io_mem,
+            reg_shift: read_usize("reg-shift").unwrap_or(0) as u32,
+            reg_io_width: read_usize("reg-io-width").unwrap_or(1) as u32,
+        };
+        if ![1, 2, 4].contains(&driver.reg_io_width) {
+            warn!("[I2C]: {}: invalid register width", node.name);
+            continue;
+        }
+
+        // Like Linux, `clock-frequency` is the frequency of the controller in the legacy
+        // binding, and that of the bus if the frequency of the controller is given otherwise.
+        let (ip_clock_hz, bus_hz) = match read_usize("opencores,ip-clock-frequency") {
+            Some(ip_clock_hz) => (
+                Some(ip_clock_hz),
+                read_usize("clock-frequency").unwrap_or(DEFAULT_BUS_HZ),
+            ),
+            None if node.property("clocks").is_none() => {
+                (read_usize("clock-frequency"), DEFAULT_BUS_HZ)
+            }
+            None => (
+                None,
+                read_usize("clock-frequency").unwrap_or(DEFAULT_BUS_HZ),
+            ),
+        };
+        driver.reset(ip_clock_hz, bus_hz);
+
+        info!("[I2C]: found OpenCores I2C controller {}", node.name);
+        crate::register_fdt_adapter(Arc::new(driver), &node);
+    }
+}
+
+impl Ocores {
+    /// Sets the prescaler if the frequency of the controller is known, and enables it.
+    fn reset(&self, ip_clock_hz: Option<usize>, bus_hz: usize) {
+        self.write(OCI2C_CONTROL, 0);
+        if let Some(ip_clock_hz) = ip_clock_hz.filter(|_| bus_hz > 0) {
+            let prescale = (ip_clock_hz / (5 * bus_hz)).saturating_sub(1);
+            self.write(OCI2C_PRELOW, prescale as u8);
+            self.write(OCI2C_PREHIGH, (prescale >> 8) as u8);
+        } else {
+            warn!("[I2C]: {}: unknown clock frequency", self.name);
+        }
+        self.write(OCI2C_CONTROL, OCI2C_CTRL_EN);
+        self.write(OCI2C_CMD, OCI2C_CMD_IACK);
+    }
+
+    /// Sends the command, and waits for its completion.
+    fn command(&self, cmd: u8) -> Result<u8, I2cError> {
+        self.write(OCI2C_CMD, cmd);
+        poll_until(|| self.read(OCI2C_STATUS) & OCI2C_STAT_TIP == 0)?;
+        let status = self.read(OCI2C_STATUS);
+        if status & OCI2C_STAT_ARBLOST != 0 {
+            return Err(I2cError::ArbitrationLost);
+        }
+        Ok(status)
+    }
+
+    fn do_transfer(&self, msgs: &mut [I2cMsg]) -> Result<(), I2cError> {
+        let nr_msgs = msgs.len();
+        for (index, msg) in msgs.iter_mut().enumerate() {
+            let is_last_msg = index == nr_msgs - 1;
+            let len = msg.len();
+
+            let is_read = matches!(msg, I2cMsg::Read { .. });
+            self.write(OCI2C_DATA, ((msg.addr() as u8) << 1) | is_read as u8);
+            let stop = if is_last_msg && len == 0 {
+                OCI2C_CMD_STOP
+            } else {
+                0
+            };
+            if self.command(OCI2C_CMD_START | OCI2C_CMD_WRITE | stop)? & OCI2C_STAT_NACK != 0 {
+                return Err(I2cError::Nack);
+            }
+
+            for byte_index in 0..len {
+                let is_last_byte = byte_index == len - 1;
+                let stop = if is_last_msg && is_last_byte {
+                    OCI2C_CMD_STOP
+                } else {
+                    0
+                };
+                match msg {
+                    I2cMsg::Write { buf, .. } => {
+                        self.write(OCI2C_DATA, buf[byte_index]);
+                        if self.command(OCI2C_CMD_WRITE | stop)? & OCI2C_STAT_NACK != 0 {
+                            return Err(I2cError::Nack);
+                        }
+                    }
+                    I2cMsg::Read { buf, .. } => {
+                        // The last byte of a read is not acknowledged.
+                        let nack = if is_last_byte { OCI2C_CMD_READ_NACK } else { 0 };
+                        self.command(OCI2C_CMD_READ | nack | stop)?;
+                        buf[byte_index] = self.read(OCI2C_DATA);
+                    }
+                }
+            }
+        }
+
+        poll_until(|| self.read(OCI2C_STATUS) & OCI2C_STAT_BUSY == 0)
+    }
+
+    fn read(&self, reg: usize) -> u8 {
+        let offset = reg << self.reg_shift;
+        let value = match self.reg_io_width {
+            1 => self.io_mem.reg::<u8>(offset).map(|reg| reg.read() as u32),
+            2 => self.io_mem.reg::<u16>(offset).map(|reg| reg.read() as u32),
+            _ => self.io_mem.reg::<u32>(offset).map(|reg| reg.read()),
+        };
+        value.unwrap_or(0) as u8
+    }
+
+    fn write(&self, reg: usize, value: u8) {
+        let offset = reg << self.reg_shift;
+        let _ = match self.reg_io_width {
+            1 => self.io_mem.reg::<u8>(offset).map(|reg| reg.write(value)),
+            2 => self
+                .io_mem
+                .reg::<u16>(offset)
+                .map(|reg| reg.write(value as u16)),
+            _ => self
+                .io_mem
+                .reg::<u32>(offset)
+                .map(|reg| reg.write(value as u32)),
+        };
+    }
+}
+
+impl I2cAdapterDriver for Ocores {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn transfer(&self, msgs: &mut [I2cMsg]) -> Result<(), I2cError> {
+        let result = self.do_transfer(msgs);
+        if result.is_err() {
+            // Release the bus.
+            self.write(OCI2C_CMD, OCI2C_CMD_STOP);
+            let _ = poll_until(|| self.read(OCI2C_STATUS) & OCI2C_STAT_BUSY == 0);
+        }
+        result
+    }
+
+    fn supports_empty_msgs(&self) -> bool {
+        true
+    }
+}
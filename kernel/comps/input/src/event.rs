@@ -0,0 +1,278 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The input events and the capabilities of the input devices.
+//!
+//! The event types and codes are the same as those of Linux (see `input-event-codes.h`),
+//! so that the events can be passed to the user space through evdev as is.
+
+use alloc::{vec, vec::Vec};
+
+use crate::key::{Key, KeyStatus};
+
+// The event types.
+pub const EV_SYN: u16 = 0x00;
+pub const EV_KEY: u16 = 0x01;
+pub const EV_REL: u16 = 0x02;
+pub const EV_ABS: u16 = 0x03;
+pub const EV_MSC: u16 = 0x04;
+pub const EV_SW: u16 = 0x05;
+pub const EV_LED: u16 = 0x11;
+pub const EV_SND: u16 = 0x12;
+pub const EV_REP: u16 = 0x14;
+pub const EV_FF: u16 = 0x15;
+pub const EV_CNT: usize = 0x20;
+
+// The synchronization events.
+pub const SYN_REPORT: u16 = 0;
+pub const SYN_DROPPED: u16 = 3;
+
+// The buttons, which share the code space with the keys.
+pub const BTN_LEFT: u16 = 0x110;
+pub const BTN_RIGHT: u16 = 0x111;
+pub const BTN_MIDDLE: u16 = 0x112;
+pub const BTN_SIDE: u16 = 0x113;
+pub const BTN_EXTRA: u16 = 0x114;
+pub const BTN_TOUCH: u16 = 0x14a;
+pub const KEY_CNT: usize = 0x300;
+
+// The relative axes.
+pub const REL_X: u16 = 0x00;
+pub const REL_Y: u16 = 0x01;
+pub const REL_HWHEEL: u16 = 0x06;
+pub const REL_WHEEL: u16 = 0x08;
+pub const REL_CNT: usize = 0x10;
+
+// The absolute axes.
+pub const ABS_X: u16 = 0x00;
+pub const ABS_Y: u16 = 0x01;
+pub const ABS_CNT: usize = 0x40;
+
+// The counts of the other event codes.
+pub const MSC_CNT: usize = 0x08;
+pub const SW_CNT: usize = 0x11;
+pub const LED_CNT: usize = 0x10;
+pub const SND_CNT: usize = 0x08;
+pub const FF_CNT: usize = 0x80;
+
+// The properties of the devices.
+pub const INPUT_PROP_POINTER: u16 = 0x00;
+pub const INPUT_PROP_DIRECT: u16 = 0x01;
+pub const INPUT_PROP_CNT: usize = 0x20;
+
+// The bus types.
+pub const BUS_USB: u16 = 0x03;
+pub const BUS_VIRTUAL: u16 = 0x06;
+
+/// An input event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputEvent {
+    /// A key of the keyboard is pressed or released.
+    KeyBoard(Key, KeyStatus),
+    /// A key or a button that is not a [`Key`] (e.g., `BTN_LEFT`) is pressed or released.
+    Button(u16, KeyStatus),
+    /// A relative axis (e.g., `REL_X`) moves by the value.
+    Relative(u16, i32),
+    /// An absolute axis (e.g., `ABS_X`) changes to the value.
+    Absolute(u16, i32),
+    /// The events since the last synchronization form a complete report.
+    Sync,
+}
+
+impl InputEvent {
+    /// Creates an event from the type, the code, and the value of a Linux input event.
+    ///
+    /// Returns `None` if the event is not supported, e.g., the repeated key events.
+    pub fn from_raw(type_: u16, code: u16, value: i32) -> Option<Self> {
+        let event = match type_ {
+            EV_SYN if code == SYN_REPORT => InputEvent::Sync,
+            EV_KEY => {
+                let status = match value {
+                    0 => KeyStatus::Released,
+                    1 => KeyStatus::Pressed,
+                    _ => return None,
+                };
+                match Key::try_from(code) {
+                    Ok(key) => InputEvent::KeyBoard(key, status),
+                    Err(_) if (code as usize) < KEY_CNT => InputEvent::Button(code, status),
+                    Err(_) => return None,
+                }
+            }
+            EV_REL if (code as usize) < REL_CNT => InputEvent::Relative(code, value),
+            EV_ABS if (code as usize) < ABS_CNT => InputEvent::Absolute(code, value),
+            _ => return None,
+        };
+        Some(event)
+    }
+
+    /// Returns the type, the code, and the value of the Linux input event.
+    pub fn to_raw(&self) -> (u16, u16, i32) {
+        let key_value = |status: &KeyStatus| match status {
+            KeyStatus::Pressed => 1,
+            KeyStatus::Released => 0,
+        };
+        match self {
+            InputEvent::KeyBoard(key, status) => (EV_KEY, *key as u16, key_value(status)),
+            InputEvent::Button(code, status) => (EV_KEY, *code, key_value(status)),
+            InputEvent::Relative(code, value) => (EV_REL, *code, *value),
+            InputEvent::Absolute(code, value) => (EV_ABS, *code, *value),
+            InputEvent::Sync => (EV_SYN, SYN_REPORT, 0),
+        }
+    }
+}
+
+/// The identity of an input device, i.e., `struct input_id` in Linux.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InputId {
+    pub bus_type: u16,
+    pub vendor: u16,
+    pub product: u16,
+    pub version: u16,
+}
+
+/// The range of an absolute axis.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AbsInfo {
+    pub minimum: i32,
+    pub maximum: i32,
+    pub fuzz: i32,
+    pub flat: i32,
+    pub resolution: i32,
+}
+
+/// The events that an input device may report.
+#[derive(Debug, Clone)]
+pub struct InputCapability {
+    props: u32,
+    keys: [u64; KEY_CNT / 64],
+    rels: u64,
+    abs: u64,
+    abs_info: [AbsInfo; ABS_CNT],
+}
+
+impl Default for InputCapability {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InputCapability {
+    /// Creates a capability with no events.
+    pub const fn new() -> Self {
+        Self {
+            props: 0,
+            keys: [0; KEY_CNT / 64],
+            rels: 0,
+            abs: 0,
+            abs_info: [AbsInfo {
+                minimum: 0,
+                maximum: 0,
+                fuzz: 0,
+                flat: 0,
+                resolution: 0,
+            }; ABS_CNT],
+        }
+    }
+
+    /// Creates the capability of a keyboard with all the [`Key`]s.
+    pub fn keyboard() -> Self {
+        let mut capability = Self::new();
+        for code in 0..KEY_CNT as u16 {
+            if Key::try_from(code).is_ok_and(|key| key != Key::Reserved) {
+                capability.set_key(code);
+            }
+        }
+        capability
+    }
+
+    /// Adds a key or a button.
+    pub fn set_key(&mut self, code: u16) {
+        if let Some(bits) = self.keys.get_mut(code as usize / 64) {
+            *bits |= 1 << (code % 64);
+        }
+    }
+
+    /// Adds a relative axis.
+    pub fn set_relative(&mut self, code: u16) {
+        if (code as usize) < REL_CNT {
+            self.rels |= 1 << code;
+        }
+    }
+
+    /// Adds an absolute axis with its range.
+    pub fn set_absolute(&mut self, code: u16, info: AbsInfo) {
+        if (code as usize) < ABS_CNT {
+            self.abs |= 1 << code;
+            self.abs_info[code as usize] = info;
+        }
+    }
+
+    /// Adds a property, e.g., `INPUT_PROP_POINTER`.
+    pub fn set_property(&mut self, prop: u16) {
+        if (prop as usize) < INPUT_PROP_CNT {
+            self.props |= 1 << prop;
+        }
+    }
+
+    /// Returns whether the event may be reported.
+    pub fn supports(&self, event: &InputEvent) -> bool {
+        let (type_, code, _) = event.to_raw();
+        match type_ {
+            EV_SYN => true,
+            EV_KEY => self.keys[code as usize / 64] & (1 << (code % 64)) != 0,
+            EV_REL => self.rels & (1 << code) != 0,
+            EV_ABS => self.abs & (1 << code) != 0,
+            _ => false,
+        }
+    }
+
+    /// Returns the range of the absolute axis, or `None` if the axis is not supported.
+    pub fn abs_info(&self, code: u16) -> Option<AbsInfo> {
+        if (code as usize) < ABS_CNT && self.abs & (1 << code) != 0 {
+            Some(self.abs_info[code as usize])
+        } else {
+            None
+        }
+    }
+
+    /// Returns the bitmap of the event codes of the event type, or of the event types if
+    /// `type_` is zero.
+    ///
+    /// The bitmap is in the layout of the `EVIOCGBIT` ioctl, i.e., an array of 64-bit longs.
+    /// Returns `None` if the event type is unknown.
+    pub fn bitmap(&self, type_: u16) -> Option<Vec<u64>> {
+        let bitmap = match type_ {
+            EV_SYN => {
+                let mut types = 1 << EV_SYN;
+                if self.keys.iter().any(|bits| *bits != 0) {
+                    types |= 1 << EV_KEY;
+                }
+                if self.rels != 0 {
+                    types |= 1 << EV_REL;
+                }
+                if self.abs != 0 {
+                    types |= 1 << EV_ABS;
+                }
+                vec![types]
+            }
+            EV_KEY => self.keys.to_vec(),
+            EV_REL => vec![self.rels],
+            EV_ABS => vec![self.abs],
+            EV_MSC => empty_bitmap(MSC_CNT),
+            EV_SW => empty_bitmap(SW_CNT),
+            EV_LED => empty_bitmap(LED_CNT),
+            EV_SND => empty_bitmap(SND_CNT),
+            EV_FF => empty_bitmap(FF_CNT),
+            _ => return None,
+        };
+        Some(bitmap)
+    }
+
+    /// Returns the bitmap of the properties.
+    pub fn props(&self) -> u32 {
+        self.props
+    }
+}
+
+fn empty_bitmap(nr_bits: usize) -> Vec<u64> {
+    vec![0; nr_bits.div_ceil(64)]
+}
@@ -1,29 +1,48 @@
 // SPDX-License-Identifier: MPL-2.0
 
 //! The input devices of Asterinas.
+//!
+//! The input drivers register their devices here, and report the [`InputEvent`]s through
+//! the registered callbacks. The events are normalized to the key, the relative, and the
+//! absolute events of Linux, and each device describes the events that it may report by an
+//! [`InputCapability`].
 #![no_std]
 #![deny(unsafe_code)]
 #![feature(fn_traits)]
 
 extern crate alloc;
 
+pub mod event;
 pub mod key;
 
 use alloc::{collections::BTreeMap, string::String, sync::Arc, vec::Vec};
 use core::{any::Any, fmt::Debug};
 
 use component::{init_component, ComponentInitError};
-use key::{Key, KeyStatus};
 use ostd::sync::SpinLock;
 use spin::Once;
 
-#[derive(Debug, Clone, Copy)]
-pub enum InputEvent {
-    KeyBoard(Key, KeyStatus),
-}
+pub use self::event::{InputCapability, InputEvent, InputId};
 
 pub trait InputDevice: Send + Sync + Any + Debug {
     fn register_callbacks(&self, function: &'static (dyn Fn(InputEvent) + Send + Sync));
+
+    /// Returns the human-readable name of the device.
+    fn name(&self) -> &str {
+        ""
+    }
+
+    /// Returns the identity of the device.
+    fn id(&self) -> InputId {
+        InputId::default()
+    }
+
+    /// Returns the events that the device may report.
+    ///
+    /// By default, the device is a keyboard.
+    fn capability(&self) -> InputCapability {
+        InputCapability::keyboard()
+    }
 }
 
 pub fn register_device(name: String, device: Arc<dyn InputDevice>) {
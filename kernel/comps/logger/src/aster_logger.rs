@@ -1,26 +1,55 @@
 // SPDX-License-Identifier: MPL-2.0
 
 use log::{Metadata, Record};
-use ostd::timer::Jiffies;
+use ostd::{
+    sync::{LocalIrqDisabled, SpinLock, SpinLockGuard},
+    timer::Jiffies,
+};
+
+use crate::{
+    filter,
+    log_buffer::{self, LOG_KERN},
+};
 
 /// The logger used for Asterinas.
 struct AsterLogger;
 
 static LOGGER: AsterLogger = AsterLogger;
 
+/// The global lock to prevent interleaving of log messages.
+static RECORD_LOCK: SpinLock<()> = SpinLock::new(());
+
+/// Locks the record lock.
+///
+/// When the kernel is panicking, the lock may be held forever by a stopped CPU, so the
+/// messages are printed without the lock if it is contended.
+fn lock_record() -> Option<SpinLockGuard<'static, (), LocalIrqDisabled>> {
+    let lock = RECORD_LOCK.disable_irq();
+    if ostd::panic::is_panicking() {
+        lock.try_lock()
+    } else {
+        Some(lock.lock())
+    }
+}
+
 impl log::Log for AsterLogger {
-    fn enabled(&self, _metadata: &Metadata) -> bool {
-        true
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        filter::is_enabled(metadata.target(), metadata.level())
     }
 
     fn log(&self, record: &Record) {
-        let timestamp = Jiffies::elapsed().as_duration().as_secs_f64();
+        if !self.enabled(record.metadata()) {
+            return;
+        }
 
-        // Use a global lock to prevent interleaving of log messages.
-        use ostd::sync::SpinLock;
-        static RECORD_LOCK: SpinLock<()> = SpinLock::new(());
-        let _lock = RECORD_LOCK.disable_irq().lock();
+        let level = filter::syslog_level(record.level());
+        log_buffer::append((LOG_KERN << 3) | level, *record.args());
+        if !filter::should_print(level) {
+            return;
+        }
 
+        let timestamp = Jiffies::elapsed().as_duration().as_secs_f64();
+        let _lock = lock_record();
         print_logs(record, timestamp);
     }
 
@@ -59,6 +88,22 @@ fn print_logs(record: &Record, timestamp: f64) {
     ));
 }
 
+/// Logs a message with the syslog priority (e.g., a message written to `/dev/kmsg`).
+///
+/// The message is recorded in the log buffer, and is printed to the console if its level
+/// is below the console log level.
+pub fn log_message(prio: u8, message: &str) {
+    let level = prio & 0x7;
+    log_buffer::append(prio, format_args!("{}", message));
+    if !filter::should_print(level) {
+        return;
+    }
+
+    let timestamp = Jiffies::elapsed().as_duration().as_secs_f64();
+    let _lock = lock_record();
+    super::_print(format_args!("[{:>10.3}] {}\n", timestamp, message));
+}
+
 pub(super) fn init() {
     ostd::logger::inject_logger(&LOGGER);
 }
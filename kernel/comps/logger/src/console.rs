@@ -7,6 +7,8 @@
 
 use core::fmt::{Arguments, Write};
 
+use spin::Once;
+
 struct VirtioConsolesPrinter;
 
 impl Write for VirtioConsolesPrinter {
@@ -18,10 +20,26 @@ impl Write for VirtioConsolesPrinter {
         for (_, device) in devices.iter() {
             device.send(s.as_bytes());
         }
+        drop(devices);
+
+        if let Some(hook) = PRINT_HOOK.get() {
+            hook(s);
+        }
         Ok(())
     }
 }
 
+static PRINT_HOOK: Once<fn(&str)> = Once::new();
+
+/// Registers a hook that receives all the output printed to the consoles.
+///
+/// This allows keeping a copy of the output elsewhere (e.g., in the persistent RAM). The
+/// printing may happen in any context (e.g., with IRQs disabled), so the hook must not sleep.
+/// Only one hook can be registered, and subsequent registrations have no effect.
+pub fn register_print_hook(hook: fn(&str)) {
+    PRINT_HOOK.call_once(|| hook);
+}
+
 /// Prints the formatted arguments to the standard output.
 pub fn _print(args: Arguments) {
     VirtioConsolesPrinter.write_fmt(args).unwrap();
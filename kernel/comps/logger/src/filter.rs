@@ -0,0 +1,170 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The filtering of the log messages.
+//!
+//! A message is first filtered by the level of its module, which is the global log level
+//! unless overridden at runtime (i.e., the dynamic debug). The messages that pass are
+//! recorded in the log buffer, and only those more important than the console log level
+//! are also printed to the console.
+
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+use log::{Level, LevelFilter};
+use ostd::sync::{Mutex, Rcu};
+use spin::Once;
+
+// The syslog levels that the log levels are mapped to.
+pub const LOGLEVEL_ERR: u8 = 3;
+pub const LOGLEVEL_WARNING: u8 = 4;
+pub const LOGLEVEL_INFO: u8 = 6;
+pub const LOGLEVEL_DEBUG: u8 = 7;
+
+/// The default console log level, which prints all messages.
+///
+/// Unlike Linux, the debug messages are printed by default, since they are only recorded
+/// if the global log level (i.e., `ostd.log_level`) enables them.
+pub const CONSOLE_LOGLEVEL_DEFAULT: u8 = LOGLEVEL_DEBUG + 1;
+
+/// The console log level used by the `quiet` kernel command line option.
+pub const CONSOLE_LOGLEVEL_QUIET: u8 = LOGLEVEL_WARNING;
+
+/// The minimum console log level that can be set.
+pub const CONSOLE_LOGLEVEL_MIN: u8 = 1;
+
+/// The default level of the messages written by the user without a level.
+pub const MESSAGE_LOGLEVEL_DEFAULT: u8 = LOGLEVEL_WARNING;
+
+static CONSOLE_LOGLEVEL: AtomicU8 = AtomicU8::new(CONSOLE_LOGLEVEL_DEFAULT);
+
+/// Whether the console is turned off by `syslog(SYSLOG_ACTION_CONSOLE_OFF)`.
+static IS_CONSOLE_OFF: AtomicBool = AtomicBool::new(false);
+
+/// The global log level.
+static DEFAULT_LEVEL: Once<LevelFilter> = Once::new();
+
+type ModuleLevels = Vec<(String, LevelFilter)>;
+
+/// The log levels of the modules overridden at runtime.
+///
+/// The levels are read with RCU instead of locks, so the logging does not deadlock even if
+/// the heap allocator logs a message while the levels are being updated.
+static MODULE_LEVELS: Once<Rcu<Box<ModuleLevels>>> = Once::new();
+
+/// The lock that serializes the updates of [`MODULE_LEVELS`].
+static MODULE_LEVELS_UPDATE_LOCK: Mutex<()> = Mutex::new(());
+
+/// Whether any module level is overridden, which allows a fast path.
+static HAS_MODULE_LEVELS: AtomicBool = AtomicBool::new(false);
+
+pub(super) fn init() {
+    DEFAULT_LEVEL.call_once(log::max_level);
+    MODULE_LEVELS.call_once(|| Rcu::new(Box::new(Vec::new())));
+
+    let cmdline = &ostd::boot::boot_info().kernel_cmdline;
+    for arg in cmdline.split(' ') {
+        if arg == "quiet" {
+            set_console_loglevel(CONSOLE_LOGLEVEL_QUIET);
+        } else if let Some(level) = arg.strip_prefix("loglevel=") {
+            if let Ok(level) = level.parse() {
+                set_console_loglevel(level);
+            }
+        }
+    }
+}
+
+/// Converts a log level to the syslog level.
+pub fn syslog_level(level: Level) -> u8 {
+    match level {
+        Level::Error => LOGLEVEL_ERR,
+        Level::Warn => LOGLEVEL_WARNING,
+        Level::Info => LOGLEVEL_INFO,
+        Level::Debug | Level::Trace => LOGLEVEL_DEBUG,
+    }
+}
+
+/// Returns the console log level.
+///
+/// The messages whose syslog levels are smaller than the console log level are printed.
+pub fn console_loglevel() -> u8 {
+    CONSOLE_LOGLEVEL.load(Ordering::Relaxed)
+}
+
+/// Sets the console log level, which is clamped to the valid range.
+pub fn set_console_loglevel(level: u8) {
+    let level = level.clamp(CONSOLE_LOGLEVEL_MIN, CONSOLE_LOGLEVEL_DEFAULT);
+    CONSOLE_LOGLEVEL.store(level, Ordering::Relaxed);
+}
+
+/// Turns the console on or off, without changing the console log level.
+pub fn set_console_enabled(is_enabled: bool) {
+    IS_CONSOLE_OFF.store(!is_enabled, Ordering::Relaxed);
+}
+
+/// Returns whether a message with the syslog level should be printed to the console.
+pub(super) fn should_print(level: u8) -> bool {
+    !IS_CONSOLE_OFF.load(Ordering::Relaxed) && level < console_loglevel()
+}
+
+/// Returns the global log level.
+pub fn default_level() -> LevelFilter {
+    DEFAULT_LEVEL.get().copied().unwrap_or_else(log::max_level)
+}
+
+/// Overrides the log level of a module and its submodules, or restores the global log level
+/// if the level is `None`.
+///
+/// The module is named by its path, e.g., `aster_nix::fs` or `ostd::mm`.
+pub fn set_module_level(module: &str, level: Option<LevelFilter>) {
+    let Some(rcu) = MODULE_LEVELS.get() else {
+        return;
+    };
+
+    let _guard = MODULE_LEVELS_UPDATE_LOCK.lock();
+    let mut module_levels = module_levels();
+    module_levels.retain(|(name, _)| name != module);
+    if let Some(level) = level {
+        module_levels.push((String::from(module), level));
+    }
+
+    // The log macros skip the messages above the maximum level without asking the logger.
+    let max_level = module_levels
+        .iter()
+        .map(|(_, level)| *level)
+        .fold(default_level(), LevelFilter::max);
+    HAS_MODULE_LEVELS.store(!module_levels.is_empty(), Ordering::Relaxed);
+    rcu.update(Box::new(module_levels));
+    log::set_max_level(max_level);
+}
+
+/// Returns the overridden log levels of the modules.
+pub fn module_levels() -> Vec<(String, LevelFilter)> {
+    let Some(rcu) = MODULE_LEVELS.get() else {
+        return Vec::new();
+    };
+    let module_levels = rcu.read();
+    (**module_levels).clone()
+}
+
+/// Returns whether a message of the level in the module (named by `target`) is enabled.
+pub(super) fn is_enabled(target: &str, level: Level) -> bool {
+    if !HAS_MODULE_LEVELS.load(Ordering::Relaxed) {
+        return level <= default_level();
+    }
+
+    let module_levels = MODULE_LEVELS.get().unwrap().read();
+    let module_level = module_levels
+        .iter()
+        .filter(|(name, _)| is_in_module(target, name))
+        .max_by_key(|(name, _)| name.len())
+        .map(|(_, level)| *level);
+    drop(module_levels);
+
+    level <= module_level.unwrap_or_else(default_level)
+}
+
+fn is_in_module(target: &str, module: &str) -> bool {
+    target
+        .strip_prefix(module)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+}
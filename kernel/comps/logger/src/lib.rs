@@ -2,9 +2,12 @@
 
 //! The logger implementation for Asterinas.
 //!
-//! This logger now has the most basic logging functionality, controls the output
-//! based on the globally set log level. Different log levels will be represented
-//! with different colors if enabling `log_color` feature.
+//! This logger filters the messages by the global log level, which can be overridden for
+//! each module at runtime (i.e., the dynamic debug). The messages are recorded in a
+//! per-CPU structured log buffer (see [`read_entry`]), which backs `/dev/kmsg` and
+//! `syslog(2)`, and are printed to the consoles if they are more important than the
+//! console log level. Different log levels will be represented with different colors if
+//! enabling `log_color` feature.
 //!
 //! This logger guarantees _atomicity_ under concurrency: messages are always
 //! printed in their entirety without being mixed with messages generated
@@ -20,11 +23,25 @@ use component::{init_component, ComponentInitError};
 
 mod aster_logger;
 mod console;
+mod filter;
+mod log_buffer;
 
-pub use console::_print;
+pub use aster_logger::log_message;
+pub use console::{_print, register_print_hook};
+pub use filter::{
+    console_loglevel, default_level, module_levels, set_console_enabled, set_console_loglevel,
+    set_module_level, syslog_level, CONSOLE_LOGLEVEL_DEFAULT, CONSOLE_LOGLEVEL_MIN,
+    MESSAGE_LOGLEVEL_DEFAULT,
+};
+pub use log_buffer::{
+    buffer_size, clear, clear_seq, first_seq, next_seq, read_entry, register_notifier, LogEntry,
+    LogReadError, LOG_KERN, LOG_LINE_MAX, LOG_USER,
+};
 
 #[init_component]
 fn init() -> Result<(), ComponentInitError> {
+    filter::init();
+    log_buffer::init();
     aster_logger::init();
     Ok(())
 }
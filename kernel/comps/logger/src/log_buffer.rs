@@ -0,0 +1,306 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The kernel log buffer.
+//!
+//! Each CPU appends its log entries to its own ring of fixed-size slots, so that logging
+//! never allocates memory and rarely contends with other CPUs. The entries of all CPUs are
+//! ordered by a global sequence number, which the readers follow to merge the rings. If a
+//! ring is full, its oldest entry is overwritten, and the readers will notice the gap.
+
+use alloc::{string::String, vec::Vec};
+use core::{
+    fmt::{self, Write},
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::Duration,
+};
+
+use ostd::{
+    cpu::{num_cpus, PinCurrentCpu},
+    sync::{LocalIrqDisabled, SpinLock, SpinLockGuard},
+    timer::{self, Jiffies},
+};
+use spin::Once;
+
+/// The number of the slots in the ring of each CPU.
+const NR_SLOTS_PER_CPU: usize = 256;
+
+/// The maximum length of the text of an entry in bytes. Longer texts are truncated.
+pub const LOG_LINE_MAX: usize = 256;
+
+/// The facility of the kernel messages, i.e., `LOG_KERN`.
+pub const LOG_KERN: u8 = 0;
+
+/// The facility of the messages written by the user, i.e., `LOG_USER`.
+pub const LOG_USER: u8 = 1;
+
+/// A log entry read from the log buffer.
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    /// The sequence number
+    pub seq: u64,
+    /// The syslog priority, i.e., the facility shifted left by 3 bits plus the level
+    pub prio: u8,
+    /// The time since boot
+    pub timestamp: Duration,
+    /// The text, without the trailing newline
+    pub text: String,
+}
+
+impl LogEntry {
+    /// Returns the syslog level of the entry.
+    pub fn level(&self) -> u8 {
+        self.prio & 0x7
+    }
+}
+
+/// An error when reading the log buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogReadError {
+    /// The entry has been overwritten. The oldest available sequence number is provided.
+    Lost(u64),
+    /// The entry has not been logged yet.
+    NotYet,
+}
+
+/// The ring of a CPU.
+struct Ring {
+    slots: Vec<Slot>,
+    /// The index of the next slot to write
+    next_slot: usize,
+    /// The entries with a smaller sequence number have been overwritten
+    first_valid_seq: u64,
+}
+
+#[derive(Clone)]
+struct Slot {
+    /// The sequence number, or `u64::MAX` if the slot is unused
+    seq: u64,
+    prio: u8,
+    /// The time since boot in jiffies
+    timestamp: u64,
+    len: usize,
+    text: [u8; LOG_LINE_MAX],
+}
+
+impl Slot {
+    const UNUSED: Slot = Slot {
+        seq: u64::MAX,
+        prio: 0,
+        timestamp: 0,
+        len: 0,
+        text: [0; LOG_LINE_MAX],
+    };
+
+    fn to_entry(&self) -> LogEntry {
+        LogEntry {
+            seq: self.seq,
+            prio: self.prio,
+            timestamp: Jiffies::new(self.timestamp).as_duration(),
+            text: String::from_utf8_lossy(&self.text[..self.len]).into_owned(),
+        }
+    }
+}
+
+static RINGS: Once<Vec<SpinLock<Ring, LocalIrqDisabled>>> = Once::new();
+
+/// The sequence number of the next entry.
+static NEXT_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// The sequence number of the first entry after the buffer is cleared.
+static CLEAR_SEQ: AtomicU64 = AtomicU64::new(0);
+
+static HAS_NEW_ENTRIES: AtomicBool = AtomicBool::new(false);
+
+static NOTIFIER: Once<fn()> = Once::new();
+
+pub(super) fn init() {
+    RINGS.call_once(|| {
+        (0..num_cpus())
+            .map(|_| {
+                SpinLock::new(Ring {
+                    slots: alloc::vec![Slot::UNUSED; NR_SLOTS_PER_CPU],
+                    next_slot: 0,
+                    first_valid_seq: 0,
+                })
+            })
+            .collect()
+    });
+    timer::register_callback(notify_readers);
+}
+
+/// Locks a ring.
+///
+/// When the kernel is panicking, the ring may be locked forever by a stopped CPU, so `None`
+/// is returned if the lock is contended.
+fn lock_ring(
+    ring: &SpinLock<Ring, LocalIrqDisabled>,
+) -> Option<SpinLockGuard<'_, Ring, LocalIrqDisabled>> {
+    if ostd::panic::is_panicking() {
+        ring.try_lock()
+    } else {
+        Some(ring.lock())
+    }
+}
+
+/// Appends an entry to the log buffer.
+///
+/// The entry is dropped if the log buffer is not initialized yet, or if the ring of the
+/// current CPU cannot be locked when the kernel is panicking.
+pub(super) fn append(prio: u8, args: fmt::Arguments) {
+    let Some(rings) = RINGS.get() else {
+        return;
+    };
+
+    let irq_guard = ostd::trap::disable_local();
+    let Some(mut ring) = lock_ring(&rings[irq_guard.current_cpu().as_usize()]) else {
+        return;
+    };
+    let next_slot = ring.next_slot;
+    ring.next_slot = (next_slot + 1) % NR_SLOTS_PER_CPU;
+
+    // The sequence number is taken with the lock held, so each ring is ordered.
+    let seq = NEXT_SEQ.fetch_add(1, Ordering::Relaxed);
+    let old_seq = ring.slots[next_slot].seq;
+    if old_seq != u64::MAX {
+        ring.first_valid_seq = old_seq + 1;
+    }
+
+    let slot = &mut ring.slots[next_slot];
+    let mut writer = SlotWriter {
+        buf: &mut slot.text,
+        len: 0,
+    };
+    let _ = writer.write_fmt(args);
+    let len = writer.len;
+    // Drop the trailing newlines, which are added by the readers.
+    slot.len = slot.text[..len]
+        .iter()
+        .rposition(|byte| *byte != b'\n')
+        .map_or(0, |pos| pos + 1);
+    slot.seq = seq;
+    slot.prio = prio;
+    slot.timestamp = Jiffies::elapsed().as_u64();
+    drop(ring);
+
+    HAS_NEW_ENTRIES.store(true, Ordering::Release);
+}
+
+/// Reads the entry with the sequence number.
+pub fn read_entry(seq: u64) -> Result<LogEntry, LogReadError> {
+    let Some(rings) = RINGS.get() else {
+        return Err(LogReadError::NotYet);
+    };
+    if seq >= NEXT_SEQ.load(Ordering::Relaxed) {
+        return Err(LogReadError::NotYet);
+    }
+
+    let mut oldest_seq = u64::MAX;
+    let mut is_lost = false;
+    for ring in rings.iter() {
+        let Some(ring) = lock_ring(ring) else {
+            continue;
+        };
+        if let Some(slot) = ring.slots.iter().find(|slot| slot.seq == seq) {
+            return Ok(slot.to_entry());
+        }
+        if ring.first_valid_seq > seq {
+            is_lost = true;
+        }
+        if let Some(slot) = ring
+            .slots
+            .iter()
+            .filter(|slot| slot.seq != u64::MAX && slot.seq > seq)
+            .min_by_key(|slot| slot.seq)
+        {
+            oldest_seq = oldest_seq.min(slot.seq);
+        }
+    }
+
+    // Otherwise, the entry is being written by another CPU.
+    if is_lost {
+        Err(LogReadError::Lost(
+            oldest_seq.min(NEXT_SEQ.load(Ordering::Relaxed)),
+        ))
+    } else {
+        Err(LogReadError::NotYet)
+    }
+}
+
+/// Returns the sequence number of the oldest available entry.
+pub fn first_seq() -> u64 {
+    let next_seq = next_seq();
+    let Some(rings) = RINGS.get() else {
+        return next_seq;
+    };
+    rings
+        .iter()
+        .filter_map(|ring| {
+            lock_ring(ring)?
+                .slots
+                .iter()
+                .filter(|slot| slot.seq != u64::MAX)
+                .map(|slot| slot.seq)
+                .min()
+        })
+        .min()
+        .unwrap_or(next_seq)
+}
+
+/// Returns the sequence number of the next entry to be logged.
+pub fn next_seq() -> u64 {
+    NEXT_SEQ.load(Ordering::Relaxed)
+}
+
+/// Returns the sequence number of the first entry after the buffer was last cleared.
+pub fn clear_seq() -> u64 {
+    CLEAR_SEQ.load(Ordering::Relaxed).max(first_seq())
+}
+
+/// Clears the log buffer.
+///
+/// The entries are not erased, but the readers that honor the clearing (e.g., `dmesg`)
+/// will not see them anymore.
+pub fn clear() {
+    CLEAR_SEQ.store(next_seq(), Ordering::Relaxed);
+}
+
+/// Returns the total size of the text that the log buffer can hold.
+pub fn buffer_size() -> usize {
+    num_cpus() * NR_SLOTS_PER_CPU * LOG_LINE_MAX
+}
+
+/// Registers a function that is called after new entries are logged.
+///
+/// The function is called in the timer interrupt handler instead of the logging context,
+/// since the logging may happen with arbitrary locks held. Only one function can be
+/// registered, and subsequent registrations have no effect.
+pub fn register_notifier(notifier: fn()) {
+    NOTIFIER.call_once(|| notifier);
+}
+
+fn notify_readers() {
+    if !HAS_NEW_ENTRIES.swap(false, Ordering::Acquire) {
+        return;
+    }
+    if let Some(notifier) = NOTIFIER.get() {
+        notifier();
+    }
+}
+
+/// A writer that writes into a slot and truncates the overlong text at a character boundary.
+struct SlotWriter<'a> {
+    buf: &'a mut [u8; LOG_LINE_MAX],
+    len: usize,
+}
+
+impl Write for SlotWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut len = s.len().min(LOG_LINE_MAX - self.len);
+        while !s.is_char_boundary(len) {
+            len -= 1;
+        }
+        self.buf[self.len..self.len + len].copy_from_slice(&s.as_bytes()[..len]);
+        self.len += len;
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The block device interface of cards.
+
+use aster_block::{
+    bio::{BioEnqueueError, BioStatus, BioType, SubmittedBio},
+    request_queue::{BioRequest, BioRequestSingleQueue, QueueLimits},
+    BlockDeviceMeta, SECTOR_SIZE,
+};
+use log::warn;
+use ostd::sync::Mutex;
+
+use crate::{card::Card, host::MmcError};
+
+/// A card exposed as a block device.
+///
+/// The requests are processed synchronously by [`MmcBlockDevice::handle_requests`], which
+/// is expected to be called in a loop by a dedicated kernel thread.
+#[derive(Debug)]
+pub struct MmcBlockDevice {
+    card: Mutex<Card>,
+    queue: BioRequestSingleQueue,
+    nr_sectors: usize,
+}
+
+impl MmcBlockDevice {
+    pub(crate) fn new(card: Card) -> Self {
+        // The card layer splits large transfers by itself, but there is no point in
+        // building requests larger than one transfer.
+        let limits = QueueLimits {
+            max_nr_sectors: card.max_blocks_per_transfer(),
+            ..QueueLimits::default()
+        };
+        Self {
+            nr_sectors: card.nr_sectors(),
+            card: Mutex::new(card),
+            queue: BioRequestSingleQueue::with_limits(limits),
+        }
+    }
+
+    /// Dequeues a request and processes it.
+    ///
+    /// This method blocks if there is no pending request.
+    pub fn handle_requests(&self) {
+        let request = self.queue.dequeue();
+        match request.type_() {
+            BioType::Read | BioType::Write => self.do_rw(request),
+            // Cards complete the writes before reporting the completion.
+            BioType::Flush => complete_all(&request, BioStatus::Complete),
+            BioType::Discard => complete_all(&request, BioStatus::NotSupported),
+        }
+    }
+
+    fn do_rw(&self, request: BioRequest) {
+        let card = self.card.lock();
+        let is_read = request.type_() == BioType::Read;
+
+        for bio in request.bios() {
+            let mut sector = bio.sid_range().start.to_raw() as usize;
+            let mut result = Ok(());
+
+            for segment in bio.segments() {
+                let dma_slice = segment.inner_dma_slice();
+                dma_slice.sync().unwrap();
+                result = if is_read {
+                    card.read_sectors(sector, dma_slice)
+                } else {
+                    card.write_sectors(sector, dma_slice)
+                };
+                if is_read {
+                    dma_slice.sync().unwrap();
+                }
+                if result.is_err() {
+                    break;
+                }
+                sector += dma_slice.nbytes() / SECTOR_SIZE;
+            }
+
+            let status = match result {
+                Ok(()) => BioStatus::Complete,
+                Err(MmcError::Unsupported) => BioStatus::NotSupported,
+                Err(err) => {
+                    warn!(
+                        "[MMC]: {:?} failed at sector {}: {:?}",
+                        bio.type_(),
+                        sector,
+                        err
+                    );
+                    BioStatus::IoError
+                }
+            };
+            bio.complete(status);
+        }
+    }
+}
+
+fn complete_all(request: &BioRequest, status: BioStatus) {
+    for bio in request.bios() {
+        bio.complete(status);
+    }
+}
+
+impl aster_block::BlockDevice for MmcBlockDevice {
+    fn enqueue(&self, bio: SubmittedBio) -> Result<(), BioEnqueueError> {
+        self.queue.enqueue(bio)
+    }
+
+    fn metadata(&self) -> BlockDeviceMeta {
+        BlockDeviceMeta {
+            max_nr_segments_per_bio: self.queue.max_nr_segments_per_bio(),
+            nr_sectors: self.nr_sectors,
+        }
+    }
+
+    fn plug(&self) {
+        self.queue.plug();
+    }
+
+    fn unplug(&self) {
+        self.queue.unplug();
+    }
+}
@@ -0,0 +1,408 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The card identification and data transfer.
+
+use alloc::sync::Arc;
+
+use aster_block::SECTOR_SIZE;
+use ostd::mm::{DmaDirection, DmaStream, DmaStreamSlice, FrameAllocOptions, VmIo};
+
+use crate::{
+    command::*,
+    host::{delay_us, poll_until, BusWidth, MmcData, MmcError, MmcHost, MmcIos},
+};
+
+/// The kinds of cards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CardKind {
+    /// Standard capacity SD card (byte addressed).
+    SdSc,
+    /// High or extended capacity SD card (block addressed).
+    SdHc,
+    /// eMMC device or MMC card.
+    Mmc,
+}
+
+/// The clock frequency (in Hz) during card identification.
+const IDENT_CLOCK: u32 = 400_000;
+/// The clock frequency (in Hz) of the default speed mode of SD cards.
+const SD_DEFAULT_CLOCK: u32 = 25_000_000;
+/// The clock frequency (in Hz) of the legacy speed mode of MMC cards.
+const MMC_LEGACY_CLOCK: u32 = 26_000_000;
+
+/// The number of times that the power up status is polled.
+const OP_COND_RETRIES: usize = 100;
+/// The delay (in microseconds) between two polls of the power up status.
+const OP_COND_DELAY_US: u64 = 10_000;
+/// The timeout (in microseconds) of waiting for the card to become ready for data.
+const READY_TIMEOUT_US: u64 = 1_000_000;
+
+/// The check pattern and the supply voltage (2.7-3.6V) of `SEND_IF_COND`.
+const IF_COND_ARG: u32 = 0x1AA;
+
+/// The index of the `BUS_WIDTH` byte in the EXT_CSD register.
+const EXT_CSD_BUS_WIDTH: u32 = 183;
+/// The offset of the `SEC_COUNT` field in the EXT_CSD register.
+const EXT_CSD_SEC_COUNT: usize = 212;
+/// The `SWITCH` access mode that writes a byte of the EXT_CSD register.
+const SWITCH_WRITE_BYTE: u32 = 0b11;
+
+/// An initialized card in the transfer state.
+#[derive(Debug)]
+pub struct Card {
+    host: Arc<dyn MmcHost>,
+    kind: CardKind,
+    /// The relative card address.
+    rca: u16,
+    /// Whether the data address is in units of sectors, instead of bytes.
+    is_block_addressed: bool,
+    nr_sectors: usize,
+}
+
+impl Card {
+    /// Identifies the card attached to `host` and brings it to the transfer state.
+    pub fn init(host: Arc<dyn MmcHost>) -> Result<Self, MmcError> {
+        host.set_ios(&MmcIos {
+            clock: IDENT_CLOCK,
+            bus_width: BusWidth::One,
+        })?;
+        // Give the card at least 74 clock cycles to power up.
+        delay_us(1_000);
+
+        if !host.card_present() {
+            return Err(MmcError::NoCard);
+        }
+
+        let mut card = Self {
+            host,
+            kind: CardKind::SdSc,
+            rca: 0,
+            is_block_addressed: false,
+            nr_sectors: 0,
+        };
+        card.go_idle()?;
+
+        let ocr = match card.sd_power_up() {
+            Ok(ocr) => ocr,
+            Err(MmcError::Timeout) => {
+                card.host.reset_on_error();
+                card.go_idle()?;
+                card.kind = CardKind::Mmc;
+                card.mmc_power_up()?
+            }
+            Err(err) => return Err(err),
+        };
+        card.is_block_addressed = ocr.contains(Ocr::CCS);
+        if card.kind == CardKind::SdSc && card.is_block_addressed {
+            card.kind = CardKind::SdHc;
+        }
+
+        card.cmd(ALL_SEND_CID, 0, ResponseType::R2)?;
+        if card.kind == CardKind::Mmc {
+            card.rca = 1;
+            card.cmd(SEND_RELATIVE_ADDR, card.rca_arg(), ResponseType::R1)?;
+        } else {
+            let resp = card.cmd(SEND_RELATIVE_ADDR, 0, ResponseType::R6)?;
+            card.rca = (resp.short() >> 16) as u16;
+        }
+
+        let csd = card.cmd(SEND_CSD, card.rca_arg(), ResponseType::R2)?;
+        card.nr_sectors = card.capacity_from_csd(&csd);
+
+        card.cmd(SELECT_CARD, card.rca_arg(), ResponseType::R1b)?;
+
+        let caps = card.host.caps();
+        let bus_width = if card.kind == CardKind::Mmc {
+            if card.is_block_addressed {
+                card.nr_sectors = card.read_mmc_sec_count()?;
+            }
+            let bus_width = if caps.bus_width_8 {
+                BusWidth::Eight
+            } else if caps.bus_width_4 {
+                BusWidth::Four
+            } else {
+                BusWidth::One
+            };
+            if bus_width != BusWidth::One {
+                let value = if bus_width == BusWidth::Eight { 2 } else { 1 };
+                let arg = (SWITCH_WRITE_BYTE << 24) | (EXT_CSD_BUS_WIDTH << 16) | (value << 8);
+                card.cmd(SWITCH, arg, ResponseType::R1b)?;
+                card.wait_ready()?;
+            }
+            bus_width
+        } else if caps.bus_width_4 {
+            card.app_cmd(SD_APP_SET_BUS_WIDTH, 0b10, ResponseType::R1)?;
+            BusWidth::Four
+        } else {
+            BusWidth::One
+        };
+
+        if !card.is_block_addressed {
+            card.cmd(SET_BLOCKLEN, SECTOR_SIZE as u32, ResponseType::R1)?;
+        }
+
+        let clock = if card.kind == CardKind::Mmc {
+            MMC_LEGACY_CLOCK
+        } else {
+            SD_DEFAULT_CLOCK
+        };
+        card.host.set_ios(&MmcIos { clock, bus_width })?;
+
+        Ok(card)
+    }
+
+    /// Returns the kind of the card.
+    pub fn kind(&self) -> CardKind {
+        self.kind
+    }
+
+    /// Returns the number of sectors of the card.
+    pub fn nr_sectors(&self) -> usize {
+        self.nr_sectors
+    }
+
+    /// Returns the maximum number of sectors in one transfer.
+    pub fn max_blocks_per_transfer(&self) -> usize {
+        self.host.caps().max_blocks_per_transfer
+    }
+
+    /// Reads the sectors starting at `start_sector` into `buffer`.
+    ///
+    /// The length of `buffer` must be a multiple of the sector size. The caller is responsible
+    /// for synchronizing `buffer`.
+    pub fn read_sectors(
+        &self,
+        start_sector: usize,
+        buffer: &DmaStreamSlice<DmaStream>,
+    ) -> Result<(), MmcError> {
+        self.transfer(start_sector, buffer, DataDirection::Read)
+    }
+
+    /// Writes `buffer` to the sectors starting at `start_sector`.
+    ///
+    /// The length of `buffer` must be a multiple of the sector size. The caller is responsible
+    /// for synchronizing `buffer`.
+    pub fn write_sectors(
+        &self,
+        start_sector: usize,
+        buffer: &DmaStreamSlice<DmaStream>,
+    ) -> Result<(), MmcError> {
+        self.transfer(start_sector, buffer, DataDirection::Write)
+    }
+
+    fn transfer(
+        &self,
+        start_sector: usize,
+        buffer: &DmaStreamSlice<DmaStream>,
+        direction: DataDirection,
+    ) -> Result<(), MmcError> {
+        debug_assert_eq!(buffer.nbytes() % SECTOR_SIZE, 0);
+        let nr_sectors = buffer.nbytes() / SECTOR_SIZE;
+        if start_sector + nr_sectors > self.nr_sectors {
+            return Err(MmcError::Unsupported);
+        }
+
+        let max_blocks = self.host.caps().max_blocks_per_transfer;
+        let mut done = 0;
+        while done < nr_sectors {
+            let nblocks = (nr_sectors - done).min(max_blocks);
+            let chunk = DmaStreamSlice::new(
+                buffer.stream().clone(),
+                buffer.offset() + done * SECTOR_SIZE,
+                nblocks * SECTOR_SIZE,
+            );
+            let data = MmcData {
+                direction,
+                block_size: SECTOR_SIZE,
+                nblocks,
+                buffer: &chunk,
+            };
+
+            let result = self.transfer_blocks(start_sector + done, &data);
+            if result.is_err() {
+                // Retry once after recovering the card to the transfer state.
+                self.recover(nblocks > 1);
+                self.transfer_blocks(start_sector + done, &data)?;
+            }
+            done += nblocks;
+        }
+        Ok(())
+    }
+
+    fn transfer_blocks(&self, sector: usize, data: &MmcData) -> Result<(), MmcError> {
+        let opcode = match (data.direction, data.nblocks > 1) {
+            (DataDirection::Read, false) => READ_SINGLE_BLOCK,
+            (DataDirection::Read, true) => READ_MULTIPLE_BLOCK,
+            (DataDirection::Write, false) => WRITE_BLOCK,
+            (DataDirection::Write, true) => WRITE_MULTIPLE_BLOCK,
+        };
+        let arg = if self.is_block_addressed {
+            sector
+        } else {
+            sector * SECTOR_SIZE
+        };
+        let arg = u32::try_from(arg).map_err(|_| MmcError::Unsupported)?;
+
+        let cmd = MmcCommand::new(opcode, arg, ResponseType::R1);
+        let resp = self.host.send_command(&cmd, Some(data))?;
+        check_status(resp.short())?;
+
+        if data.direction == DataDirection::Write {
+            self.wait_ready()?;
+        }
+        Ok(())
+    }
+
+    /// Brings the card back to the transfer state after a failed transfer.
+    fn recover(&self, is_multiple: bool) {
+        self.host.reset_on_error();
+        if is_multiple {
+            let _ = self.cmd(STOP_TRANSMISSION, 0, ResponseType::R1b);
+        }
+        let _ = self.wait_ready();
+    }
+
+    fn go_idle(&self) -> Result<(), MmcError> {
+        self.cmd(GO_IDLE_STATE, 0, ResponseType::None)?;
+        delay_us(1_000);
+        Ok(())
+    }
+
+    /// Runs the power up sequence of SD cards.
+    fn sd_power_up(&self) -> Result<Ocr, MmcError> {
+        // Only version 2.00 or later cards respond to `SEND_IF_COND`.
+        let is_v2 = match self.cmd(SEND_IF_COND, IF_COND_ARG, ResponseType::R7) {
+            Ok(resp) if resp.short() & 0xFFF == IF_COND_ARG => true,
+            Ok(_) => return Err(MmcError::Unsupported),
+            Err(MmcError::Timeout) => {
+                self.host.reset_on_error();
+                false
+            }
+            Err(err) => return Err(err),
+        };
+
+        let mut arg = Ocr::VDD_32_34;
+        if is_v2 {
+            arg |= Ocr::CCS;
+        }
+        for _ in 0..OP_COND_RETRIES {
+            let resp = self.app_cmd(SD_APP_OP_COND, arg.bits(), ResponseType::R3)?;
+            let ocr = Ocr::from_bits_truncate(resp.short());
+            if ocr.contains(Ocr::POWER_UP_DONE) {
+                return Ok(ocr);
+            }
+            delay_us(OP_COND_DELAY_US);
+        }
+        Err(MmcError::Timeout)
+    }
+
+    /// Runs the power up sequence of MMC cards.
+    fn mmc_power_up(&self) -> Result<Ocr, MmcError> {
+        let arg = Ocr::CCS | Ocr::VDD_27_36 | Ocr::MMC_VDD_165_195;
+        for _ in 0..OP_COND_RETRIES {
+            let resp = self.cmd(MMC_SEND_OP_COND, arg.bits(), ResponseType::R3)?;
+            let ocr = Ocr::from_bits_truncate(resp.short());
+            if ocr.contains(Ocr::POWER_UP_DONE) {
+                return Ok(ocr);
+            }
+            delay_us(OP_COND_DELAY_US);
+        }
+        Err(MmcError::Timeout)
+    }
+
+    /// Computes the number of sectors from the CSD register.
+    ///
+    /// For MMC cards larger than 2 GiB, the result is not accurate and the capacity should
+    /// be read from the EXT_CSD register instead.
+    fn capacity_from_csd(&self, csd: &MmcResponse) -> usize {
+        let structure = csd.long_bits(126, 2);
+        if self.kind != CardKind::Mmc && structure == 1 {
+            // CSD version 2.0: the capacity is `(C_SIZE + 1) * 512 KiB`.
+            let c_size = csd.long_bits(48, 22) as usize;
+            return (c_size + 1) * 1024;
+        }
+
+        let c_size = csd.long_bits(62, 12) as usize;
+        let c_size_mult = csd.long_bits(47, 3) as usize;
+        let read_bl_len = csd.long_bits(80, 4) as usize;
+        ((c_size + 1) << (c_size_mult + 2 + read_bl_len)) / SECTOR_SIZE
+    }
+
+    /// Reads the `SEC_COUNT` field of the EXT_CSD register of MMC cards.
+    fn read_mmc_sec_count(&self) -> Result<usize, MmcError> {
+        let stream = {
+            let segment = FrameAllocOptions::new().alloc_segment(1).unwrap();
+            DmaStream::map(segment.into(), DmaDirection::FromDevice, false).unwrap()
+        };
+        let ext_csd = DmaStreamSlice::new(stream, 0, SECTOR_SIZE);
+        let data = MmcData {
+            direction: DataDirection::Read,
+            block_size: SECTOR_SIZE,
+            nblocks: 1,
+            buffer: &ext_csd,
+        };
+
+        ext_csd.sync().unwrap();
+        let cmd = MmcCommand::new(MMC_SEND_EXT_CSD, 0, ResponseType::R1);
+        let resp = self.host.send_command(&cmd, Some(&data))?;
+        check_status(resp.short())?;
+        ext_csd.sync().unwrap();
+
+        let sec_count: u32 = ext_csd.read_val(EXT_CSD_SEC_COUNT).unwrap();
+        Ok(u32::from_le(sec_count) as usize)
+    }
+
+    /// Waits until the card is ready for data in the transfer state.
+    fn wait_ready(&self) -> Result<(), MmcError> {
+        poll_until(READY_TIMEOUT_US, || {
+            let resp = self
+                .cmd(SEND_STATUS, self.rca_arg(), ResponseType::R1)
+                .ok()?;
+            let status = CardStatus::from_bits_truncate(resp.short());
+            (status.contains(CardStatus::READY_FOR_DATA) && status.state() == CardState::Transfer)
+                .then_some(())
+        })
+    }
+
+    fn rca_arg(&self) -> u32 {
+        (self.rca as u32) << 16
+    }
+
+    fn cmd(&self, opcode: u8, arg: u32, resp_type: ResponseType) -> Result<MmcResponse, MmcError> {
+        let resp = self
+            .host
+            .send_command(&MmcCommand::new(opcode, arg, resp_type), None)?;
+        if matches!(resp_type, ResponseType::R1 | ResponseType::R1b) {
+            check_status(resp.short())?;
+        }
+        Ok(resp)
+    }
+
+    /// Sends an application-specific command, which is prefixed with `APP_CMD`.
+    fn app_cmd(
+        &self,
+        opcode: u8,
+        arg: u32,
+        resp_type: ResponseType,
+    ) -> Result<MmcResponse, MmcError> {
+        let resp = self.cmd(APP_CMD, self.rca_arg(), ResponseType::R1)?;
+        if !CardStatus::from_bits_truncate(resp.short()).contains(CardStatus::APP_CMD) {
+            return Err(MmcError::Unsupported);
+        }
+        self.cmd(opcode, arg, resp_type)
+    }
+}
+
+/// Checks the error bits of the card status in an R1 response.
+fn check_status(status: u32) -> Result<(), MmcError> {
+    let status = CardStatus::from_bits_truncate(status);
+    if status.intersects(CardStatus::ERRORS) {
+        log::warn!("[MMC]: card status error: {:?}", status);
+        return Err(if status.contains(CardStatus::COM_CRC_ERROR) {
+            MmcError::Crc
+        } else {
+            MmcError::Io
+        });
+    }
+    Ok(())
+}
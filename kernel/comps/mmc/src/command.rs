@@ -0,0 +1,204 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! SD/MMC commands and responses.
+
+use bitflags::bitflags;
+
+/// A command sent to the card.
+#[derive(Debug, Clone, Copy)]
+pub struct MmcCommand {
+    /// The command index (e.g., 17 for `READ_SINGLE_BLOCK`).
+    pub opcode: u8,
+    /// The 32-bit command argument.
+    pub arg: u32,
+    /// The expected response type.
+    pub resp_type: ResponseType,
+}
+
+impl MmcCommand {
+    pub const fn new(opcode: u8, arg: u32, resp_type: ResponseType) -> Self {
+        Self {
+            opcode,
+            arg,
+            resp_type,
+        }
+    }
+}
+
+/// The response types defined by the SD and MMC specifications.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseType {
+    /// No response.
+    None,
+    /// Normal response (48 bits).
+    R1,
+    /// Normal response with an optional busy signal on DAT0.
+    R1b,
+    /// CID or CSD register (136 bits).
+    R2,
+    /// OCR register (48 bits, no CRC).
+    R3,
+    /// Published RCA response (48 bits).
+    R6,
+    /// Card interface condition (48 bits).
+    R7,
+}
+
+impl ResponseType {
+    /// Returns whether the card sends a response.
+    pub fn has_response(self) -> bool {
+        self != Self::None
+    }
+
+    /// Returns whether the response is 136 bits long.
+    pub fn is_long(self) -> bool {
+        self == Self::R2
+    }
+
+    /// Returns whether the card may signal busy after the response.
+    pub fn is_busy(self) -> bool {
+        self == Self::R1b
+    }
+
+    /// Returns whether the response is protected by CRC7.
+    pub fn has_crc(self) -> bool {
+        !matches!(self, Self::None | Self::R3)
+    }
+
+    /// Returns whether the response echoes the command index.
+    pub fn has_index(self) -> bool {
+        !matches!(self, Self::None | Self::R2 | Self::R3)
+    }
+}
+
+/// A response received from the card.
+///
+/// For 136-bit responses, `words[0]` holds bits 127..96 of the register (i.e., the CID or
+/// CSD), and `words[3]` holds bits 31..0. The host drivers are responsible for normalizing
+/// the controller-specific layouts to this one. For 48-bit responses, only `words[0]` is
+/// valid and holds the 32-bit card status or register content.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MmcResponse {
+    pub words: [u32; 4],
+}
+
+impl MmcResponse {
+    /// Returns the 32-bit content of a short response.
+    pub fn short(&self) -> u32 {
+        self.words[0]
+    }
+
+    /// Extracts `len` bits starting at bit `start` of a long response.
+    pub fn long_bits(&self, start: usize, len: usize) -> u32 {
+        debug_assert!(len > 0 && len <= 32 && start + len <= 128);
+        let mut value: u64 = 0;
+        for bit in (start..start + len).rev() {
+            let word = self.words[3 - bit / 32];
+            value = (value << 1) | ((word >> (bit % 32)) & 1) as u64;
+        }
+        value as u32
+    }
+}
+
+/// The transfer direction of the data phase.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataDirection {
+    Read,
+    Write,
+}
+
+bitflags! {
+    /// The card status returned in R1 responses.
+    pub struct CardStatus: u32 {
+        const OUT_OF_RANGE      = 1 << 31;
+        const ADDRESS_ERROR     = 1 << 30;
+        const BLOCK_LEN_ERROR   = 1 << 29;
+        const ERASE_SEQ_ERROR   = 1 << 28;
+        const ERASE_PARAM       = 1 << 27;
+        const WP_VIOLATION      = 1 << 26;
+        const CARD_IS_LOCKED    = 1 << 25;
+        const LOCK_UNLOCK_FAILED= 1 << 24;
+        const COM_CRC_ERROR     = 1 << 23;
+        const ILLEGAL_COMMAND   = 1 << 22;
+        const CARD_ECC_FAILED   = 1 << 21;
+        const CC_ERROR          = 1 << 20;
+        const ERROR             = 1 << 19;
+        const READY_FOR_DATA    = 1 << 8;
+        const APP_CMD           = 1 << 5;
+    }
+}
+
+impl CardStatus {
+    /// The error bits of the card status.
+    pub const ERRORS: Self = Self::from_bits_truncate(0xFDF9_0008);
+
+    /// Returns the current state of the card.
+    pub fn state(&self) -> CardState {
+        match (self.bits() >> 9) & 0xF {
+            0 => CardState::Idle,
+            1 => CardState::Ready,
+            2 => CardState::Ident,
+            3 => CardState::Standby,
+            4 => CardState::Transfer,
+            5 => CardState::Data,
+            6 => CardState::Receive,
+            7 => CardState::Program,
+            8 => CardState::Disconnect,
+            _ => CardState::Reserved,
+        }
+    }
+}
+
+/// The card states defined by the SD and MMC specifications.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CardState {
+    Idle,
+    Ready,
+    Ident,
+    Standby,
+    Transfer,
+    Data,
+    Receive,
+    Program,
+    Disconnect,
+    Reserved,
+}
+
+bitflags! {
+    /// The bits of the OCR register.
+    pub struct Ocr: u32 {
+        /// The card has finished the power up routine.
+        const POWER_UP_DONE     = 1 << 31;
+        /// The card is high capacity (SDHC/SDXC) or sector addressed (eMMC).
+        const CCS               = 1 << 30;
+        /// The voltage window of 3.2V to 3.4V.
+        const VDD_32_34         = 0b11 << 20;
+        /// The full voltage window of 2.7V to 3.6V.
+        const VDD_27_36         = 0x1FF << 15;
+        /// The dual voltage range of eMMC devices.
+        const MMC_VDD_165_195   = 1 << 7;
+    }
+}
+
+// The command indices used by the card layer.
+pub const GO_IDLE_STATE: u8 = 0;
+pub const MMC_SEND_OP_COND: u8 = 1;
+pub const ALL_SEND_CID: u8 = 2;
+pub const SEND_RELATIVE_ADDR: u8 = 3;
+pub const SWITCH: u8 = 6;
+pub const SELECT_CARD: u8 = 7;
+pub const SEND_IF_COND: u8 = 8;
+pub const MMC_SEND_EXT_CSD: u8 = 8;
+pub const SEND_CSD: u8 = 9;
+pub const STOP_TRANSMISSION: u8 = 12;
+pub const SEND_STATUS: u8 = 13;
+pub const SET_BLOCKLEN: u8 = 16;
+pub const READ_SINGLE_BLOCK: u8 = 17;
+pub const READ_MULTIPLE_BLOCK: u8 = 18;
+pub const WRITE_BLOCK: u8 = 24;
+pub const WRITE_MULTIPLE_BLOCK: u8 = 25;
+pub const APP_CMD: u8 = 55;
+
+// The application-specific command indices (sent after `APP_CMD`).
+pub const SD_APP_SET_BUS_WIDTH: u8 = 6;
+pub const SD_APP_OP_COND: u8 = 41;
@@ -0,0 +1,471 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The Synopsys DesignWare Mobile Storage Host Controller (DW MSHC) driver.
+//!
+//! This controller is used by the StarFive JH7110 SoC (VisionFive 2) and several other
+//! RISC-V SoCs. Data transfers use the internal DMA controller (IDMAC) in the chained
+//! descriptor mode. The descriptor format (32-bit or 64-bit) is selected by the address
+//! configuration of the hardware, which is reported in the `HCON` register.
+
+use alloc::string::String;
+
+use ostd::{
+    io::MmioRegion,
+    mm::{DmaDirection, DmaStream, FrameAllocOptions, HasDaddr, VmIo, VmIoOnce, PAGE_SIZE},
+};
+
+use super::{poll_until, BusWidth, HostCaps, MmcData, MmcError, MmcHost, MmcIos};
+use crate::command::{DataDirection, MmcCommand, MmcResponse, GO_IDLE_STATE};
+
+/// The DW MSHC register offsets.
+mod regs {
+    pub const CTRL: usize = 0x00;
+    pub const PWREN: usize = 0x04;
+    pub const CLKDIV: usize = 0x08;
+    pub const CLKSRC: usize = 0x0C;
+    pub const CLKENA: usize = 0x10;
+    pub const TMOUT: usize = 0x14;
+    pub const CTYPE: usize = 0x18;
+    pub const BLKSIZ: usize = 0x1C;
+    pub const BYTCNT: usize = 0x20;
+    pub const INTMASK: usize = 0x24;
+    pub const CMDARG: usize = 0x28;
+    pub const CMD: usize = 0x2C;
+    pub const RESP0: usize = 0x30;
+    pub const RINTSTS: usize = 0x44;
+    pub const STATUS: usize = 0x48;
+    pub const FIFOTH: usize = 0x4C;
+    pub const CDETECT: usize = 0x50;
+    pub const VERID: usize = 0x6C;
+    pub const HCON: usize = 0x70;
+    pub const BMOD: usize = 0x80;
+    pub const DBADDR: usize = 0x88;
+    // The IDMAC registers after `DBADDR` are shifted if the address configuration is 64-bit.
+    pub const IDSTS_32: usize = 0x8C;
+    pub const IDINTEN_32: usize = 0x90;
+    pub const IDSTS_64: usize = 0x90;
+    pub const IDINTEN_64: usize = 0x94;
+}
+
+// The bits of the control register.
+const CTRL_RESET: u32 = 1 << 0;
+const CTRL_FIFO_RESET: u32 = 1 << 1;
+const CTRL_DMA_RESET: u32 = 1 << 2;
+const CTRL_INT_ENABLE: u32 = 1 << 4;
+const CTRL_USE_IDMAC: u32 = 1 << 25;
+const CTRL_ALL_RESET: u32 = CTRL_RESET | CTRL_FIFO_RESET | CTRL_DMA_RESET;
+
+// The bits of the command register.
+const CMD_START: u32 = 1 << 31;
+const CMD_USE_HOLD_REG: u32 = 1 << 29;
+const CMD_UPD_CLK: u32 = 1 << 21;
+const CMD_INIT: u32 = 1 << 15;
+const CMD_PRV_DAT_WAIT: u32 = 1 << 13;
+const CMD_SEND_STOP: u32 = 1 << 12;
+const CMD_DAT_WR: u32 = 1 << 10;
+const CMD_DAT_EXP: u32 = 1 << 9;
+const CMD_RESP_CRC: u32 = 1 << 8;
+const CMD_RESP_LONG: u32 = 1 << 7;
+const CMD_RESP_EXP: u32 = 1 << 6;
+
+// The bits of the raw interrupt status register.
+const INT_RE: u32 = 1 << 1;
+const INT_CMD_DONE: u32 = 1 << 2;
+const INT_DTO: u32 = 1 << 3;
+const INT_RCRC: u32 = 1 << 6;
+const INT_DCRC: u32 = 1 << 7;
+const INT_RTO: u32 = 1 << 8;
+const INT_DRTO: u32 = 1 << 9;
+const INT_HTO: u32 = 1 << 10;
+const INT_FRUN: u32 = 1 << 11;
+const INT_HLE: u32 = 1 << 12;
+const INT_SBE: u32 = 1 << 13;
+const INT_ACD: u32 = 1 << 14;
+const INT_EBE: u32 = 1 << 15;
+const INT_ALL: u32 = 0xFFFF_FFFF;
+const INT_CMD_ERRORS: u32 = INT_RE | INT_RCRC | INT_RTO | INT_HLE;
+const INT_DATA_ERRORS: u32 = INT_DCRC | INT_DRTO | INT_HTO | INT_FRUN | INT_SBE | INT_EBE;
+
+// The bits of the status register.
+const STATUS_DATA_BUSY: u32 = 1 << 9;
+
+// The bits of the bus mode register.
+const BMOD_SWR: u32 = 1 << 0;
+const BMOD_FB: u32 = 1 << 1;
+const BMOD_DE: u32 = 1 << 7;
+
+// The bits of the IDMAC descriptors.
+const DESC_DIC: u32 = 1 << 1;
+const DESC_LD: u32 = 1 << 2;
+const DESC_FS: u32 = 1 << 3;
+const DESC_CH: u32 = 1 << 4;
+const DESC_OWN: u32 = 1 << 31;
+
+/// The maximum length of the data pointed by an IDMAC descriptor.
+const DESC_MAX_LEN: usize = 4096;
+
+/// The timeout for a command (in microseconds).
+const CMD_TIMEOUT_US: u64 = 100_000;
+/// The timeout for a data transfer (in microseconds).
+const DATA_TIMEOUT_US: u64 = 2_000_000;
+
+/// A DesignWare Mobile Storage Host Controller.
+#[derive(Debug)]
+pub struct DwMshc {
+    name: String,
+    io_mem: MmioRegion,
+    /// The frequency (in Hz) of the clock fed to the controller.
+    bus_hz: u32,
+    caps: HostCaps,
+    /// Whether the IDMAC uses 64-bit addresses and descriptors.
+    is_64bit: bool,
+    has_cd: bool,
+    /// The IDMAC descriptor table.
+    ///
+    /// Commands are serialized by the card layer, so the table is never shared
+    /// by two transfers.
+    desc_table: DmaStream,
+}
+
+impl DwMshc {
+    /// Resets and initializes the controller.
+    pub fn new(
+        name: String,
+        io_mem: MmioRegion,
+        bus_hz: u32,
+        max_clock: u32,
+        max_bus_width: BusWidth,
+        has_cd: bool,
+    ) -> Result<Self, MmcError> {
+        let verid: u32 = io_mem.read_once(regs::VERID).unwrap();
+        let hcon: u32 = io_mem.read_once(regs::HCON).unwrap();
+        let is_64bit = (hcon >> 27) & 1 != 0;
+
+        let desc_table = {
+            let segment = FrameAllocOptions::new().alloc_segment(1).unwrap();
+            DmaStream::map(segment.into(), DmaDirection::Bidirectional, false).unwrap()
+        };
+
+        let max_descs = PAGE_SIZE / if is_64bit { 32 } else { 16 };
+        let host = Self {
+            name,
+            io_mem,
+            bus_hz,
+            caps: HostCaps {
+                bus_width_4: max_bus_width != BusWidth::One,
+                bus_width_8: max_bus_width == BusWidth::Eight,
+                max_clock: max_clock.min(bus_hz),
+                max_blocks_per_transfer: max_descs * DESC_MAX_LEN / 512,
+            },
+            is_64bit,
+            has_cd,
+            desc_table,
+        };
+
+        host.write(regs::PWREN, 1);
+        host.reset(CTRL_ALL_RESET)?;
+
+        // The receive watermark is initialized to `FIFO depth - 1` by the hardware.
+        let fifo_depth = ((host.read(regs::FIFOTH) >> 16) & 0xFFF) + 1;
+        let fifoth = (2 << 28) | ((fifo_depth / 2 - 1) << 16) | (fifo_depth / 2);
+        host.write(regs::FIFOTH, fifoth);
+
+        // We poll the status bits, so no interrupt signals are generated.
+        host.write(regs::RINTSTS, INT_ALL);
+        host.write(regs::INTMASK, 0);
+        host.write(regs::TMOUT, 0xFFFF_FFFF);
+        host.write(regs::CTRL, host.read(regs::CTRL) & !CTRL_INT_ENABLE);
+
+        host.write(regs::BMOD, BMOD_SWR);
+        host.write(host.idsts_offset(), INT_ALL);
+        host.write(host.idinten_offset(), 0);
+
+        log::info!(
+            "[DW MSHC]: {}: version {:#x}, FIFO depth {}, 64-bit DMA: {}",
+            host.name,
+            verid & 0xFFFF,
+            fifo_depth,
+            host.is_64bit
+        );
+        Ok(host)
+    }
+
+    fn read(&self, offset: usize) -> u32 {
+        self.io_mem.read_once(offset).unwrap()
+    }
+
+    fn write(&self, offset: usize, value: u32) {
+        self.io_mem.write_once(offset, &value).unwrap();
+    }
+
+    fn idsts_offset(&self) -> usize {
+        if self.is_64bit {
+            regs::IDSTS_64
+        } else {
+            regs::IDSTS_32
+        }
+    }
+
+    fn idinten_offset(&self) -> usize {
+        if self.is_64bit {
+            regs::IDINTEN_64
+        } else {
+            regs::IDINTEN_32
+        }
+    }
+
+    fn reset(&self, bits: u32) -> Result<(), MmcError> {
+        self.write(regs::CTRL, self.read(regs::CTRL) | bits);
+        poll_until(CMD_TIMEOUT_US, || {
+            (self.read(regs::CTRL) & bits == 0).then_some(())
+        })
+    }
+
+    /// Sends a command to the controller and waits for the controller to accept it.
+    fn start_command(&self, cmd: u32, arg: u32) -> Result<(), MmcError> {
+        self.write(regs::CMDARG, arg);
+        self.write(regs::CMD, cmd | CMD_START);
+        poll_until(CMD_TIMEOUT_US, || {
+            (self.read(regs::CMD) & CMD_START == 0).then_some(())
+        })?;
+
+        if self.read(regs::RINTSTS) & INT_HLE != 0 {
+            self.write(regs::RINTSTS, INT_HLE);
+            return Err(MmcError::Io);
+        }
+        Ok(())
+    }
+
+    /// Informs the card clock domain of the new clock settings.
+    fn update_clock(&self) -> Result<(), MmcError> {
+        self.start_command(CMD_UPD_CLK | CMD_PRV_DAT_WAIT, 0)
+    }
+
+    fn set_clock(&self, clock: u32) -> Result<(), MmcError> {
+        self.write(regs::CLKENA, 0);
+        self.update_clock()?;
+        if clock == 0 {
+            return Ok(());
+        }
+
+        // The card clock is `bus_hz / (2 * div)`, or `bus_hz` if `div` is zero.
+        let div = if clock >= self.bus_hz {
+            0
+        } else {
+            self.bus_hz.div_ceil(2 * clock).min(0xFF)
+        };
+        self.write(regs::CLKDIV, div);
+        self.write(regs::CLKSRC, 0);
+        self.update_clock()?;
+
+        self.write(regs::CLKENA, 1);
+        self.update_clock()
+    }
+
+    /// Fills the chained IDMAC descriptors for the data buffer.
+    fn prepare_idmac(&self, data: &MmcData) -> Result<(), MmcError> {
+        let desc_size = if self.is_64bit { 32 } else { 16 };
+        let nbytes = data.nbytes();
+        let ndescs = nbytes.div_ceil(DESC_MAX_LEN);
+        if ndescs * desc_size > PAGE_SIZE {
+            return Err(MmcError::Unsupported);
+        }
+
+        let table_daddr = self.desc_table.daddr();
+        let start_daddr = data.buffer.daddr();
+        for index in 0..ndescs {
+            let offset = index * DESC_MAX_LEN;
+            let len = (nbytes - offset).min(DESC_MAX_LEN) as u32;
+            let mut flags = DESC_OWN | DESC_CH | DESC_DIC;
+            if index == 0 {
+                flags |= DESC_FS;
+            }
+            if index == ndescs - 1 {
+                flags |= DESC_LD;
+            }
+
+            let daddr = (start_daddr + offset) as u64;
+            let next = if index == ndescs - 1 {
+                0
+            } else {
+                (table_daddr + (index + 1) * desc_size) as u64
+            };
+            if !self.is_64bit && daddr + len as u64 > u32::MAX as u64 {
+                return Err(MmcError::Unsupported);
+            }
+
+            let desc: [u32; 8] = if self.is_64bit {
+                [
+                    flags,
+                    0,
+                    len,
+                    0,
+                    daddr as u32,
+                    (daddr >> 32) as u32,
+                    next as u32,
+                    (next >> 32) as u32,
+                ]
+            } else {
+                [flags, len, daddr as u32, next as u32, 0, 0, 0, 0]
+            };
+            let words = desc_size / size_of::<u32>();
+            for (i, word) in desc.iter().take(words).enumerate() {
+                self.desc_table
+                    .write_val(index * desc_size + i * size_of::<u32>(), word)
+                    .unwrap();
+            }
+        }
+        self.desc_table.sync(0..ndescs * desc_size).unwrap();
+
+        self.reset(CTRL_FIFO_RESET | CTRL_DMA_RESET)?;
+        self.write(self.idsts_offset(), INT_ALL);
+        self.write(regs::CTRL, self.read(regs::CTRL) | CTRL_USE_IDMAC);
+        self.write(regs::BMOD, BMOD_DE | BMOD_FB);
+        self.write(regs::DBADDR, table_daddr as u32);
+        if self.is_64bit {
+            self.write(regs::DBADDR + 4, (table_daddr as u64 >> 32) as u32);
+        }
+
+        self.write(regs::BLKSIZ, data.block_size as u32);
+        self.write(regs::BYTCNT, nbytes as u32);
+        Ok(())
+    }
+
+    /// Waits for all of the `bits` in the raw interrupt status register, or for any of the
+    /// `errors`.
+    fn wait_int(&self, bits: u32, errors: u32, timeout_us: u64) -> Result<(), MmcError> {
+        let status = poll_until(timeout_us, || {
+            let status = self.read(regs::RINTSTS);
+            (status & bits == bits || status & errors != 0).then_some(status)
+        })?;
+        self.write(regs::RINTSTS, status & (bits | errors));
+
+        if status & errors == 0 {
+            Ok(())
+        } else if status & (INT_RTO | INT_DRTO | INT_HTO) != 0 {
+            Err(MmcError::Timeout)
+        } else if status & (INT_RCRC | INT_DCRC) != 0 {
+            Err(MmcError::Crc)
+        } else {
+            Err(MmcError::Io)
+        }
+    }
+
+    fn read_response(&self, cmd: &MmcCommand) -> MmcResponse {
+        let mut resp = MmcResponse::default();
+        if !cmd.resp_type.has_response() {
+            return resp;
+        }
+        if !cmd.resp_type.is_long() {
+            resp.words[0] = self.read(regs::RESP0);
+            return resp;
+        }
+
+        // `RESP3` holds bits 127..96 of the register.
+        for i in 0..4 {
+            resp.words[3 - i] = self.read(regs::RESP0 + i * 4);
+        }
+        resp
+    }
+
+    fn finish_data(&self, data: &MmcData) -> Result<(), MmcError> {
+        let mut bits = INT_DTO;
+        if data.nblocks > 1 {
+            bits |= INT_ACD;
+        }
+        let result = self.wait_int(bits, INT_DATA_ERRORS, DATA_TIMEOUT_US);
+        self.write(regs::CTRL, self.read(regs::CTRL) & !CTRL_USE_IDMAC);
+        self.write(regs::BMOD, 0);
+        result?;
+
+        if data.direction == DataDirection::Write {
+            poll_until(DATA_TIMEOUT_US, || {
+                (self.read(regs::STATUS) & STATUS_DATA_BUSY == 0).then_some(())
+            })?;
+        }
+        Ok(())
+    }
+}
+
+impl MmcHost for DwMshc {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn caps(&self) -> HostCaps {
+        self.caps
+    }
+
+    fn card_present(&self) -> bool {
+        !self.has_cd || self.read(regs::CDETECT) & 1 == 0
+    }
+
+    fn set_ios(&self, ios: &MmcIos) -> Result<(), MmcError> {
+        self.set_clock(ios.clock.min(self.caps.max_clock))?;
+
+        let ctype = match ios.bus_width {
+            BusWidth::One => 0,
+            BusWidth::Four => 1 << 0,
+            BusWidth::Eight => 1 << 16,
+        };
+        self.write(regs::CTYPE, ctype);
+        Ok(())
+    }
+
+    fn send_command(
+        &self,
+        cmd: &MmcCommand,
+        data: Option<&MmcData>,
+    ) -> Result<MmcResponse, MmcError> {
+        poll_until(CMD_TIMEOUT_US, || {
+            (self.read(regs::STATUS) & STATUS_DATA_BUSY == 0).then_some(())
+        })?;
+        self.write(regs::RINTSTS, INT_ALL);
+
+        let mut flags = cmd.opcode as u32 | CMD_USE_HOLD_REG | CMD_PRV_DAT_WAIT;
+        if cmd.opcode == GO_IDLE_STATE {
+            flags |= CMD_INIT;
+        }
+        if cmd.resp_type.has_response() {
+            flags |= CMD_RESP_EXP;
+        }
+        if cmd.resp_type.is_long() {
+            flags |= CMD_RESP_LONG;
+        }
+        if cmd.resp_type.has_crc() {
+            flags |= CMD_RESP_CRC;
+        }
+
+        if let Some(data) = data {
+            self.prepare_idmac(data)?;
+            flags |= CMD_DAT_EXP;
+            if data.direction == DataDirection::Write {
+                flags |= CMD_DAT_WR;
+            }
+            if data.nblocks > 1 {
+                flags |= CMD_SEND_STOP;
+            }
+        }
+
+        self.start_command(flags, cmd.arg)?;
+        self.wait_int(INT_CMD_DONE, INT_CMD_ERRORS, CMD_TIMEOUT_US)?;
+        let resp = self.read_response(cmd);
+
+        if let Some(data) = data {
+            self.finish_data(data)?;
+        } else if cmd.resp_type.is_busy() {
+            poll_until(DATA_TIMEOUT_US, || {
+                (self.read(regs::STATUS) & STATUS_DATA_BUSY == 0).then_some(())
+            })?;
+        }
+
+        Ok(resp)
+    }
+
+    fn reset_on_error(&self) {
+        self.write(regs::CTRL, self.read(regs::CTRL) & !CTRL_USE_IDMAC);
+        self.write(regs::BMOD, BMOD_SWR);
+        let _ = self.reset(CTRL_FIFO_RESET | CTRL_DMA_RESET);
+        self.write(regs::RINTSTS, INT_ALL);
+    }
+}
@@ -0,0 +1,136 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! SD/MMC host controllers.
+
+pub mod dw_mshc;
+pub mod sdhci;
+
+use core::{fmt::Debug, hint::spin_loop};
+
+use ostd::mm::{DmaStream, DmaStreamSlice};
+
+use crate::command::{DataDirection, MmcCommand, MmcResponse};
+
+/// An SD/MMC host controller.
+///
+/// A host sends commands to the card on its slot and moves the data of the data phase.
+/// Host drivers are not expected to understand the meaning of the commands, except for
+/// the layout of the responses.
+pub trait MmcHost: Send + Sync + Debug {
+    /// Returns the name of the host.
+    fn name(&self) -> &str;
+
+    /// Returns the capabilities of the host.
+    fn caps(&self) -> HostCaps;
+
+    /// Returns whether a card is present in the slot.
+    fn card_present(&self) -> bool;
+
+    /// Applies the bus settings.
+    fn set_ios(&self, ios: &MmcIos) -> Result<(), MmcError>;
+
+    /// Sends a command and waits for its completion.
+    ///
+    /// If `data` is provided, the data phase is also performed and waited for. For multi-block
+    /// transfers, the host must stop the transmission with `STOP_TRANSMISSION` by itself (e.g.,
+    /// using the auto-CMD12 feature of the controller).
+    fn send_command(
+        &self,
+        cmd: &MmcCommand,
+        data: Option<&MmcData>,
+    ) -> Result<MmcResponse, MmcError>;
+
+    /// Resets the command and data circuits after an error.
+    fn reset_on_error(&self);
+}
+
+/// The capabilities of a host.
+#[derive(Debug, Clone, Copy)]
+pub struct HostCaps {
+    /// Whether the 4-bit data bus is supported.
+    pub bus_width_4: bool,
+    /// Whether the 8-bit data bus is supported.
+    pub bus_width_8: bool,
+    /// The maximum clock frequency (in Hz) of the card clock.
+    pub max_clock: u32,
+    /// The maximum number of blocks in one transfer.
+    pub max_blocks_per_transfer: usize,
+}
+
+/// The bus settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MmcIos {
+    /// The card clock (in Hz). Zero stops the clock.
+    pub clock: u32,
+    /// The data bus width.
+    pub bus_width: BusWidth,
+}
+
+/// The data bus width.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BusWidth {
+    One,
+    Four,
+    Eight,
+}
+
+/// The data phase of a command.
+#[derive(Debug)]
+pub struct MmcData<'a> {
+    /// The transfer direction.
+    pub direction: DataDirection,
+    /// The size of a block (in bytes).
+    pub block_size: usize,
+    /// The number of blocks.
+    pub nblocks: usize,
+    /// The physically contiguous DMA buffer of `block_size * nblocks` bytes.
+    ///
+    /// The caller is responsible for synchronizing the buffer before and after the transfer.
+    pub buffer: &'a DmaStreamSlice<DmaStream>,
+}
+
+impl MmcData<'_> {
+    /// Returns the total number of bytes.
+    pub fn nbytes(&self) -> usize {
+        self.block_size * self.nblocks
+    }
+}
+
+/// The errors of SD/MMC operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MmcError {
+    /// There is no card in the slot.
+    NoCard,
+    /// The card does not respond in time.
+    Timeout,
+    /// The CRC check of a response or of the data fails.
+    Crc,
+    /// The card or the controller reports an error.
+    Io,
+    /// The card or the request is not supported.
+    Unsupported,
+}
+
+/// Polls `cond` until it returns `Some`, or until `timeout_us` microseconds elapse.
+pub(crate) fn poll_until<T>(
+    timeout_us: u64,
+    mut cond: impl FnMut() -> Option<T>,
+) -> Result<T, MmcError> {
+    let freq = ostd::arch::tsc_freq().max(1);
+    let deadline = ostd::arch::read_tsc() + timeout_us.saturating_mul(freq) / 1_000_000;
+    loop {
+        if let Some(value) = cond() {
+            return Ok(value);
+        }
+        if ostd::arch::read_tsc() > deadline {
+            // Check once more in case that we were preempted for a long time.
+            return cond().ok_or(MmcError::Timeout);
+        }
+        spin_loop();
+    }
+}
+
+/// Waits for at least `us` microseconds.
+pub(crate) fn delay_us(us: u64) {
+    let _ = poll_until::<()>(us, || None);
+}
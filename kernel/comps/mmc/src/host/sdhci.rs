@@ -0,0 +1,466 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The SD Host Controller Interface (SDHCI) driver.
+//!
+//! Data transfers use ADMA2 with a descriptor table in a streaming DMA mapping. The
+//! 64-bit descriptor format is used if the controller supports 64-bit system addresses,
+//! since DRAM may be located above 4 GiB on RISC-V boards.
+
+use alloc::string::String;
+
+use ostd::{
+    io::MmioRegion,
+    mm::{DmaDirection, DmaStream, FrameAllocOptions, HasDaddr, VmIo, VmIoOnce, PAGE_SIZE},
+};
+
+use super::{poll_until, BusWidth, HostCaps, MmcData, MmcError, MmcHost, MmcIos};
+use crate::command::{DataDirection, MmcCommand, MmcResponse, ResponseType, GO_IDLE_STATE};
+
+/// The SDHCI register offsets.
+mod regs {
+    pub const BLOCK_SIZE: usize = 0x04;
+    pub const BLOCK_COUNT: usize = 0x06;
+    pub const ARGUMENT: usize = 0x08;
+    pub const TRANSFER_MODE: usize = 0x0C;
+    pub const COMMAND: usize = 0x0E;
+    pub const RESPONSE: usize = 0x10;
+    pub const PRESENT_STATE: usize = 0x24;
+    pub const HOST_CONTROL: usize = 0x28;
+    pub const POWER_CONTROL: usize = 0x29;
+    pub const CLOCK_CONTROL: usize = 0x2C;
+    pub const TIMEOUT_CONTROL: usize = 0x2E;
+    pub const SOFTWARE_RESET: usize = 0x2F;
+    pub const INT_STATUS: usize = 0x30;
+    pub const INT_ENABLE: usize = 0x34;
+    pub const SIGNAL_ENABLE: usize = 0x38;
+    pub const CAPABILITIES: usize = 0x40;
+    pub const ADMA_ADDRESS: usize = 0x58;
+    pub const HOST_VERSION: usize = 0xFE;
+}
+
+// The bits of the transfer mode register.
+const TRNS_DMA: u16 = 1 << 0;
+const TRNS_BLK_CNT_EN: u16 = 1 << 1;
+const TRNS_AUTO_CMD12: u16 = 1 << 2;
+const TRNS_READ: u16 = 1 << 4;
+const TRNS_MULTI: u16 = 1 << 5;
+
+// The bits of the command register.
+const CMD_RESP_NONE: u16 = 0b00;
+const CMD_RESP_LONG: u16 = 0b01;
+const CMD_RESP_SHORT: u16 = 0b10;
+const CMD_RESP_SHORT_BUSY: u16 = 0b11;
+const CMD_CRC: u16 = 1 << 3;
+const CMD_INDEX: u16 = 1 << 4;
+const CMD_DATA: u16 = 1 << 5;
+
+// The bits of the present state register.
+const PRESENT_CMD_INHIBIT: u32 = 1 << 0;
+const PRESENT_DATA_INHIBIT: u32 = 1 << 1;
+const PRESENT_CARD_INSERTED: u32 = 1 << 16;
+
+// The bits of the host control register.
+const HOST_CTRL_4BIT: u8 = 1 << 1;
+const HOST_CTRL_HISPD: u8 = 1 << 2;
+const HOST_CTRL_DMA_MASK: u8 = 0b11 << 3;
+const HOST_CTRL_ADMA32: u8 = 0b10 << 3;
+const HOST_CTRL_ADMA64: u8 = 0b11 << 3;
+const HOST_CTRL_8BIT: u8 = 1 << 5;
+
+// The bits of the power control register.
+const POWER_ON: u8 = 1 << 0;
+const POWER_330: u8 = 0b111 << 1;
+
+// The bits of the clock control register.
+const CLOCK_INT_EN: u16 = 1 << 0;
+const CLOCK_INT_STABLE: u16 = 1 << 1;
+const CLOCK_CARD_EN: u16 = 1 << 2;
+
+// The bits of the software reset register.
+const RESET_ALL: u8 = 1 << 0;
+const RESET_CMD: u8 = 1 << 1;
+const RESET_DATA: u8 = 1 << 2;
+
+// The bits of the interrupt status register.
+const INT_CMD_COMPLETE: u32 = 1 << 0;
+const INT_XFER_COMPLETE: u32 = 1 << 1;
+const INT_ERROR: u32 = 1 << 15;
+const INT_CMD_TIMEOUT: u32 = 1 << 16;
+const INT_CMD_CRC: u32 = 1 << 17;
+const INT_DATA_TIMEOUT: u32 = 1 << 20;
+const INT_DATA_CRC: u32 = 1 << 21;
+const INT_ALL: u32 = 0xFFFF_FFFF;
+
+// The bits of the capabilities register.
+const CAPS_8BIT: u32 = 1 << 18;
+const CAPS_ADMA2: u32 = 1 << 19;
+const CAPS_64BIT: u32 = 1 << 28;
+
+// The bits of the ADMA2 descriptor attributes.
+const ADMA2_VALID: u16 = 1 << 0;
+const ADMA2_END: u16 = 1 << 1;
+const ADMA2_ACT_TRAN: u16 = 0b10 << 4;
+
+/// The maximum length of the data pointed by an ADMA2 descriptor.
+const ADMA2_MAX_LEN: usize = 0x8000;
+
+/// The timeout for a command (in microseconds).
+const CMD_TIMEOUT_US: u64 = 100_000;
+/// The timeout for a data transfer (in microseconds).
+const DATA_TIMEOUT_US: u64 = 2_000_000;
+
+/// An SDHCI-compatible host controller.
+#[derive(Debug)]
+pub struct Sdhci {
+    name: String,
+    io_mem: MmioRegion,
+    /// The specification version (0 for 1.00, 1 for 2.00, 2 for 3.00, etc.).
+    spec_version: u8,
+    /// The base clock (in Hz).
+    base_clock: u32,
+    caps: HostCaps,
+    is_64bit: bool,
+    has_cd: bool,
+    /// The ADMA2 descriptor table.
+    ///
+    /// Commands are serialized by the card layer, so the table is never shared
+    /// by two transfers.
+    adma_table: DmaStream,
+}
+
+impl Sdhci {
+    /// Resets and initializes the controller.
+    ///
+    /// If the capabilities register does not report the base clock, `fallback_clock` is used.
+    pub fn new(
+        name: String,
+        io_mem: MmioRegion,
+        fallback_clock: Option<u32>,
+        max_bus_width: BusWidth,
+        has_cd: bool,
+    ) -> Result<Self, MmcError> {
+        io_mem.write_once(regs::SOFTWARE_RESET, &RESET_ALL).unwrap();
+        poll_until(CMD_TIMEOUT_US, || {
+            let reset: u8 = io_mem.read_once(regs::SOFTWARE_RESET).unwrap();
+            (reset & RESET_ALL == 0).then_some(())
+        })?;
+
+        let version: u16 = io_mem.read_once(regs::HOST_VERSION).unwrap();
+        let spec_version = (version & 0xFF) as u8;
+        let capabilities: u32 = io_mem.read_once(regs::CAPABILITIES).unwrap();
+        if capabilities & CAPS_ADMA2 == 0 {
+            log::warn!("[SDHCI]: {}: ADMA2 is not supported", name);
+            return Err(MmcError::Unsupported);
+        }
+
+        let base_clock_mhz = if spec_version >= 2 {
+            (capabilities >> 8) & 0xFF
+        } else {
+            (capabilities >> 8) & 0x3F
+        };
+        let base_clock = match (base_clock_mhz, fallback_clock) {
+            (0, Some(clock)) => clock,
+            (0, None) => {
+                log::warn!("[SDHCI]: {}: unknown base clock", name);
+                return Err(MmcError::Unsupported);
+            }
+            (mhz, _) => mhz * 1_000_000,
+        };
+
+        let caps = HostCaps {
+            bus_width_4: max_bus_width != BusWidth::One,
+            bus_width_8: max_bus_width == BusWidth::Eight && capabilities & CAPS_8BIT != 0,
+            max_clock: base_clock,
+            max_blocks_per_transfer: u16::MAX as usize,
+        };
+
+        // The descriptor table occupies one page, which is enough for
+        // `PAGE_SIZE / 12 * ADMA2_MAX_LEN` bytes (more than 10 MiB).
+        let adma_table = {
+            let segment = FrameAllocOptions::new().alloc_segment(1).unwrap();
+            DmaStream::map(segment.into(), DmaDirection::Bidirectional, false).unwrap()
+        };
+
+        let host = Self {
+            name,
+            io_mem,
+            spec_version,
+            base_clock,
+            caps,
+            is_64bit: capabilities & CAPS_64BIT != 0,
+            has_cd,
+            adma_table,
+        };
+
+        host.write8(regs::POWER_CONTROL, POWER_330 | POWER_ON);
+        host.write8(regs::TIMEOUT_CONTROL, 0x0E);
+        // We poll the status bits, so no interrupt signals are generated.
+        host.write32(regs::INT_ENABLE, INT_ALL);
+        host.write32(regs::SIGNAL_ENABLE, 0);
+
+        let mut host_control = host.read8(regs::HOST_CONTROL) & !HOST_CTRL_DMA_MASK;
+        host_control |= if host.is_64bit {
+            HOST_CTRL_ADMA64
+        } else {
+            HOST_CTRL_ADMA32
+        };
+        host.write8(regs::HOST_CONTROL, host_control);
+
+        log::info!(
+            "[SDHCI]: {}: version {}, base clock {} Hz, 64-bit DMA: {}",
+            host.name,
+            host.spec_version + 1,
+            host.base_clock,
+            host.is_64bit
+        );
+        Ok(host)
+    }
+
+    fn read8(&self, offset: usize) -> u8 {
+        self.io_mem.read_once(offset).unwrap()
+    }
+
+    fn write8(&self, offset: usize, value: u8) {
+        self.io_mem.write_once(offset, &value).unwrap();
+    }
+
+    fn read16(&self, offset: usize) -> u16 {
+        self.io_mem.read_once(offset).unwrap()
+    }
+
+    fn write16(&self, offset: usize, value: u16) {
+        self.io_mem.write_once(offset, &value).unwrap();
+    }
+
+    fn read32(&self, offset: usize) -> u32 {
+        self.io_mem.read_once(offset).unwrap()
+    }
+
+    fn write32(&self, offset: usize, value: u32) {
+        self.io_mem.write_once(offset, &value).unwrap();
+    }
+
+    fn set_clock(&self, clock: u32) -> Result<(), MmcError> {
+        self.write16(regs::CLOCK_CONTROL, 0);
+        if clock == 0 {
+            return Ok(());
+        }
+
+        let divider_bits = if self.spec_version >= 2 {
+            // 10-bit divided clock mode: card clock = base / (2 * N).
+            let mut n = 0u32;
+            if self.base_clock > clock {
+                n = self.base_clock.div_ceil(2 * clock).min(0x3FF);
+            }
+            (((n & 0xFF) << 8) | (((n >> 8) & 0x3) << 6)) as u16
+        } else {
+            // 8-bit power-of-two divider: card clock = base / (2 * N).
+            let mut n = 0u32;
+            if self.base_clock > clock {
+                n = 1;
+                while n < 0x80 && self.base_clock / (2 * n) > clock {
+                    n <<= 1;
+                }
+            }
+            (n << 8) as u16
+        };
+
+        self.write16(regs::CLOCK_CONTROL, divider_bits | CLOCK_INT_EN);
+        poll_until(CMD_TIMEOUT_US, || {
+            (self.read16(regs::CLOCK_CONTROL) & CLOCK_INT_STABLE != 0).then_some(())
+        })?;
+        self.write16(
+            regs::CLOCK_CONTROL,
+            divider_bits | CLOCK_INT_EN | CLOCK_CARD_EN,
+        );
+        Ok(())
+    }
+
+    /// Fills the ADMA2 descriptor table for the data buffer.
+    fn prepare_adma(&self, data: &MmcData) -> Result<(), MmcError> {
+        let desc_size = if self.is_64bit { 12 } else { 8 };
+        let nbytes = data.nbytes();
+        let ndescs = nbytes.div_ceil(ADMA2_MAX_LEN);
+        if ndescs * desc_size > PAGE_SIZE {
+            return Err(MmcError::Unsupported);
+        }
+
+        let start_daddr = data.buffer.daddr();
+        for index in 0..ndescs {
+            let offset = index * ADMA2_MAX_LEN;
+            let len = (nbytes - offset).min(ADMA2_MAX_LEN);
+            let mut attr = ADMA2_VALID | ADMA2_ACT_TRAN;
+            if index == ndescs - 1 {
+                attr |= ADMA2_END;
+            }
+
+            let daddr = (start_daddr + offset) as u64;
+            if !self.is_64bit && daddr + len as u64 > u32::MAX as u64 {
+                return Err(MmcError::Unsupported);
+            }
+
+            let desc_offset = index * desc_size;
+            self.adma_table.write_val(desc_offset, &attr).unwrap();
+            self.adma_table
+                .write_val(desc_offset + 2, &(len as u16))
+                .unwrap();
+            self.adma_table
+                .write_val(desc_offset + 4, &(daddr as u32))
+                .unwrap();
+            if self.is_64bit {
+                self.adma_table
+                    .write_val(desc_offset + 8, &((daddr >> 32) as u32))
+                    .unwrap();
+            }
+        }
+        self.adma_table.sync(0..ndescs * desc_size).unwrap();
+
+        let table_daddr = self.adma_table.daddr() as u64;
+        self.write32(regs::ADMA_ADDRESS, table_daddr as u32);
+        if self.is_64bit {
+            self.write32(regs::ADMA_ADDRESS + 4, (table_daddr >> 32) as u32);
+        }
+        Ok(())
+    }
+
+    /// Waits for any of the `bits` in the interrupt status register, or for an error.
+    fn wait_int(&self, bits: u32, timeout_us: u64) -> Result<(), MmcError> {
+        let status = poll_until(timeout_us, || {
+            let status = self.read32(regs::INT_STATUS);
+            (status & (bits | INT_ERROR) != 0).then_some(status)
+        })?;
+
+        if status & INT_ERROR != 0 {
+            self.write32(regs::INT_STATUS, INT_ALL);
+            return Err(if status & (INT_CMD_TIMEOUT | INT_DATA_TIMEOUT) != 0 {
+                MmcError::Timeout
+            } else if status & (INT_CMD_CRC | INT_DATA_CRC) != 0 {
+                MmcError::Crc
+            } else {
+                MmcError::Io
+            });
+        }
+
+        self.write32(regs::INT_STATUS, status & bits);
+        Ok(())
+    }
+
+    fn read_response(&self, resp_type: ResponseType) -> MmcResponse {
+        let mut resp = MmcResponse::default();
+        if !resp_type.has_response() {
+            return resp;
+        }
+        if !resp_type.is_long() {
+            resp.words[0] = self.read32(regs::RESPONSE);
+            return resp;
+        }
+
+        // The controller strips the CRC of 136-bit responses, so the register
+        // bits 119..0 hold the response bits 127..8.
+        let raw: [u32; 4] = core::array::from_fn(|i| self.read32(regs::RESPONSE + i * 4));
+        resp.words[0] = (raw[3] << 8) | (raw[2] >> 24);
+        resp.words[1] = (raw[2] << 8) | (raw[1] >> 24);
+        resp.words[2] = (raw[1] << 8) | (raw[0] >> 24);
+        resp.words[3] = raw[0] << 8;
+        resp
+    }
+}
+
+impl MmcHost for Sdhci {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn caps(&self) -> HostCaps {
+        self.caps
+    }
+
+    fn card_present(&self) -> bool {
+        !self.has_cd || self.read32(regs::PRESENT_STATE) & PRESENT_CARD_INSERTED != 0
+    }
+
+    fn set_ios(&self, ios: &MmcIos) -> Result<(), MmcError> {
+        self.set_clock(ios.clock.min(self.caps.max_clock))?;
+
+        let mut host_control = self.read8(regs::HOST_CONTROL);
+        host_control &= !(HOST_CTRL_4BIT | HOST_CTRL_8BIT | HOST_CTRL_HISPD);
+        match ios.bus_width {
+            BusWidth::One => {}
+            BusWidth::Four => host_control |= HOST_CTRL_4BIT,
+            BusWidth::Eight => host_control |= HOST_CTRL_8BIT,
+        }
+        if ios.clock > 25_000_000 {
+            host_control |= HOST_CTRL_HISPD;
+        }
+        self.write8(regs::HOST_CONTROL, host_control);
+        Ok(())
+    }
+
+    fn send_command(
+        &self,
+        cmd: &MmcCommand,
+        data: Option<&MmcData>,
+    ) -> Result<MmcResponse, MmcError> {
+        let mut inhibit = PRESENT_CMD_INHIBIT;
+        if data.is_some() || cmd.resp_type.is_busy() {
+            inhibit |= PRESENT_DATA_INHIBIT;
+        }
+        poll_until(CMD_TIMEOUT_US, || {
+            (self.read32(regs::PRESENT_STATE) & inhibit == 0).then_some(())
+        })?;
+        self.write32(regs::INT_STATUS, INT_ALL);
+
+        let mut flags = match cmd.resp_type {
+            ResponseType::None => CMD_RESP_NONE,
+            ResponseType::R2 => CMD_RESP_LONG,
+            ResponseType::R1b => CMD_RESP_SHORT_BUSY,
+            _ => CMD_RESP_SHORT,
+        };
+        if cmd.resp_type.has_crc() {
+            flags |= CMD_CRC;
+        }
+        if cmd.resp_type.has_index() {
+            flags |= CMD_INDEX;
+        }
+
+        if let Some(data) = data {
+            self.prepare_adma(data)?;
+
+            let mut mode = TRNS_DMA;
+            if data.nblocks > 1 {
+                mode |= TRNS_BLK_CNT_EN | TRNS_MULTI | TRNS_AUTO_CMD12;
+            }
+            if data.direction == DataDirection::Read {
+                mode |= TRNS_READ;
+            }
+            // Set the SDMA buffer boundary to 512 KiB, which is not used by ADMA2.
+            self.write16(regs::BLOCK_SIZE, (7 << 12) | data.block_size as u16);
+            self.write16(regs::BLOCK_COUNT, data.nblocks as u16);
+            self.write16(regs::TRANSFER_MODE, mode);
+            flags |= CMD_DATA;
+        } else if cmd.opcode != GO_IDLE_STATE {
+            self.write16(regs::TRANSFER_MODE, 0);
+        }
+
+        self.write32(regs::ARGUMENT, cmd.arg);
+        self.write16(regs::COMMAND, ((cmd.opcode as u16) << 8) | flags);
+
+        self.wait_int(INT_CMD_COMPLETE, CMD_TIMEOUT_US)?;
+        let resp = self.read_response(cmd.resp_type);
+
+        if data.is_some() {
+            self.wait_int(INT_XFER_COMPLETE, DATA_TIMEOUT_US)?;
+        } else if cmd.resp_type.is_busy() {
+            self.wait_int(INT_XFER_COMPLETE, DATA_TIMEOUT_US)?;
+        }
+
+        Ok(resp)
+    }
+
+    fn reset_on_error(&self) {
+        self.write8(regs::SOFTWARE_RESET, RESET_CMD | RESET_DATA);
+        let _ = poll_until(CMD_TIMEOUT_US, || {
+            (self.read8(regs::SOFTWARE_RESET) & (RESET_CMD | RESET_DATA) == 0).then_some(())
+        });
+    }
+}
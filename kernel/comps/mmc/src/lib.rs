@@ -0,0 +1,89 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The SD/MMC subsystem of Asterinas.
+//!
+//! This crate drives SD cards and eMMC devices attached to SD/MMC host controllers.
+//! It is organized in three layers:
+//!
+//! - The host layer (`host`) abstracts the controllers. Each controller driver implements
+//!   the [`MmcHost`] trait, which sends a single command (optionally with a data transfer)
+//!   to the card. Currently, the standard SDHCI controller and the Synopsys DesignWare
+//!   Mobile Storage Host Controller (used by the StarFive JH7110 on VisionFive 2) are supported.
+//! - The card layer (`card`) runs the card identification state machine and issues
+//!   the block read/write commands.
+//! - The block layer (`block`) exposes each initialized card as a block device named
+//!   `mmcblkN` in `aster-block`.
+//!
+//! Controllers are discovered through the device tree. Data is transferred by DMA, and
+//! the DMA buffers are synchronized with the CPU caches by the `DmaStream` API, which uses
+//! the Zicbom cache-block management instructions on non-coherent platforms.
+//!
+//! There is no interrupt controller support on RISC-V yet, so the controllers are polled.
+#![no_std]
+#![deny(unsafe_code)]
+
+extern crate alloc;
+
+mod block;
+mod card;
+mod command;
+mod host;
+#[cfg(target_arch = "riscv64")]
+mod probe;
+
+use alloc::{format, sync::Arc};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use aster_block::bio::bio_segment_pool_init;
+use component::{init_component, ComponentInitError};
+use log::{error, info};
+
+use self::card::Card;
+pub use self::{
+    block::MmcBlockDevice,
+    host::{MmcError, MmcHost},
+};
+
+#[init_component]
+fn mmc_component_init() -> Result<(), ComponentInitError> {
+    #[cfg(target_arch = "riscv64")]
+    for host in probe::probe_hosts() {
+        register_host(host);
+    }
+    Ok(())
+}
+
+/// Initializes the card attached to `host` and registers it as a block device.
+pub fn register_host(host: Arc<dyn MmcHost>) {
+    let card = match Card::init(host.clone()) {
+        Ok(card) => card,
+        Err(MmcError::NoCard) => {
+            info!("[MMC]: No card in host {}", host.name());
+            return;
+        }
+        Err(err) => {
+            error!(
+                "[MMC]: Card initialization failed on host {}: {:?}",
+                host.name(),
+                err
+            );
+            return;
+        }
+    };
+
+    let index = NEXT_INDEX.fetch_add(1, Ordering::Relaxed);
+    let name = format!("mmcblk{}", index);
+    info!(
+        "[MMC]: {}: {:?} card, {} sectors, host {}",
+        name,
+        card.kind(),
+        card.nr_sectors(),
+        host.name()
+    );
+
+    bio_segment_pool_init();
+    aster_block::register_device(name, Arc::new(MmcBlockDevice::new(card)));
+}
+
+/// The index of the next `mmcblkN` device.
+static NEXT_INDEX: AtomicUsize = AtomicUsize::new(0);
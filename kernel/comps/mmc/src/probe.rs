@@ -0,0 +1,104 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Discovery of SD/MMC host controllers in the device tree.
+
+use alloc::{string::ToString, sync::Arc, vec::Vec};
+
+use fdt::node::FdtNode;
+use log::{info, warn};
+use ostd::{arch::boot::DEVICE_TREE, io::MmioRegion};
+
+use crate::host::{dw_mshc::DwMshc, sdhci::Sdhci, BusWidth, MmcHost};
+
+/// The compatible strings of SDHCI-compatible controllers.
+const SDHCI_COMPATIBLES: &[&str] = &[
+    "snps,dwcmshc-sdhci",
+    "thead,th1520-dwcmshc",
+    "sophgo,sg2042-dwcmshc",
+    "arasan,sdhci-5.1",
+    "arasan,sdhci-8.9a",
+    "sdhci",
+];
+
+/// The compatible strings of DesignWare MSHC controllers.
+const DW_MSHC_COMPATIBLES: &[&str] = &["snps,dw-mshc", "starfive,jh7110-mmc"];
+
+/// The clock frequency (in Hz) that is assumed if the device tree does not provide one.
+const DEFAULT_CLOCK: u32 = 50_000_000;
+
+/// Probes the SD/MMC host controllers described in the device tree.
+pub(crate) fn probe_hosts() -> Vec<Arc<dyn MmcHost>> {
+    let mut hosts: Vec<Arc<dyn MmcHost>> = Vec::new();
+
+    for node in DEVICE_TREE.get().unwrap().all_nodes() {
+        let Some(compatible) = node.compatible() else {
+            continue;
+        };
+        let is_sdhci = compatible.all().any(|c| SDHCI_COMPATIBLES.contains(&c));
+        let is_dw_mshc = compatible.all().any(|c| DW_MSHC_COMPATIBLES.contains(&c));
+        if !is_sdhci && !is_dw_mshc {
+            continue;
+        }
+        if node
+            .property("status")
+            .and_then(|prop| prop.as_str())
+            .is_some_and(|status| status != "okay" && status != "ok")
+        {
+            continue;
+        }
+
+        let io_mem = match MmioRegion::claim_fdt_reg(&node) {
+            Ok(io_mem) => io_mem,
+            Err(err) => {
+                warn!(
+                    "[MMC]: {}: cannot claim the registers: {:?}",
+                    node.name, err
+                );
+                continue;
+            }
+        };
+
+        let name = node.name.to_string();
+        let clock = property_u32(&node, "clock-frequency");
+        let max_clock = property_u32(&node, "max-frequency");
+        let bus_width = match property_u32(&node, "bus-width") {
+            Some(8) => BusWidth::Eight,
+            Some(4) | None => BusWidth::Four,
+            Some(_) => BusWidth::One,
+        };
+        let has_cd =
+            node.property("non-removable").is_none() && node.property("broken-cd").is_none();
+
+        let result = if is_dw_mshc {
+            let bus_hz = clock.unwrap_or(DEFAULT_CLOCK);
+            DwMshc::new(
+                name,
+                io_mem,
+                bus_hz,
+                max_clock.unwrap_or(bus_hz),
+                bus_width,
+                has_cd,
+            )
+            .map(|host| Arc::new(host) as Arc<dyn MmcHost>)
+        } else {
+            Sdhci::new(name, io_mem, clock, bus_width, has_cd)
+                .map(|host| Arc::new(host) as Arc<dyn MmcHost>)
+        };
+
+        match result {
+            Ok(host) => {
+                info!("[MMC]: found host controller {}", host.name());
+                hosts.push(host);
+            }
+            Err(err) => warn!("[MMC]: {}: initialization failed: {:?}", node.name, err),
+        }
+    }
+
+    hosts
+}
+
+fn property_u32(node: &FdtNode, name: &str) -> Option<u32> {
+    node.property(name)
+        .and_then(|prop| prop.as_usize())
+        .map(|value| value as u32)
+}
@@ -0,0 +1,225 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The registered MTD devices.
+
+use alloc::{string::String, sync::Arc, vec};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{EccEngine, MtdDriver, MtdError, MtdPartitionInfo, MtdType};
+
+/// A registered MTD device, i.e., a partition of a flash.
+///
+/// The offsets of the operations are relative to the start of the partition.
+pub struct MtdDevice {
+    index: usize,
+    name: String,
+    driver: Arc<dyn MtdDriver>,
+    ecc: Option<Arc<dyn EccEngine>>,
+    /// The offset (in bytes) of the partition in the flash
+    offset: u64,
+    size: u64,
+    read_only: bool,
+    ecc_stats: EccStats,
+}
+
+/// The statistics of the ECC, which are the same as `struct mtd_ecc_stats` of Linux.
+#[derive(Debug, Default)]
+pub struct EccStats {
+    /// The number of the corrected bit flips
+    pub corrected: AtomicU64,
+    /// The number of the steps that cannot be corrected
+    pub failed: AtomicU64,
+}
+
+impl MtdDevice {
+    pub(crate) fn new(
+        index: usize,
+        driver: Arc<dyn MtdDriver>,
+        ecc: Option<Arc<dyn EccEngine>>,
+        info: MtdPartitionInfo,
+    ) -> Self {
+        Self {
+            index,
+            name: info.name,
+            driver,
+            ecc,
+            offset: info.offset,
+            size: info.size,
+            read_only: info.read_only,
+            ecc_stats: EccStats::default(),
+        }
+    }
+
+    /// Returns the index of the device.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the name of the device.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the type of the flash.
+    pub fn type_(&self) -> MtdType {
+        self.driver.type_()
+    }
+
+    /// Returns the offset (in bytes) of the device in the flash.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Returns the size (in bytes) of the device.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Returns the size (in bytes) of the erase blocks.
+    pub fn erase_size(&self) -> u32 {
+        self.driver.erase_size()
+    }
+
+    /// Returns the minimal size (in bytes) of the writes.
+    pub fn write_size(&self) -> u32 {
+        self.driver.write_size()
+    }
+
+    /// Returns the size (in bytes) of the OOB area of each page.
+    pub fn oob_size(&self) -> u32 {
+        self.driver.oob_size()
+    }
+
+    /// Returns whether the device is read-only.
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Returns the statistics of the ECC.
+    pub fn ecc_stats(&self) -> &EccStats {
+        &self.ecc_stats
+    }
+
+    /// Reads the bytes at the offset.
+    ///
+    /// If the flash has an ECC engine, the bit flips are corrected, and the data is still
+    /// read even if some bit flips cannot be corrected, in which case
+    /// [`MtdError::EccError`] is returned after the read.
+    pub fn read(&self, offset: u64, buf: &mut [u8]) -> Result<(), MtdError> {
+        self.check_range(offset, buf.len() as u64)?;
+        match self.ecc.as_ref() {
+            Some(ecc) => self.read_with_ecc(ecc.as_ref(), self.offset + offset, buf),
+            None => self.driver.read(self.offset + offset, buf),
+        }
+    }
+
+    /// Writes the bytes at the offset, which must have been erased.
+    ///
+    /// If the flash has an ECC engine, the range must be aligned to the pages.
+    pub fn write(&self, offset: u64, buf: &[u8]) -> Result<(), MtdError> {
+        if self.read_only {
+            return Err(MtdError::ReadOnly);
+        }
+        self.check_range(offset, buf.len() as u64)?;
+        let write_size = self.write_size() as u64;
+        if offset % write_size != 0 || buf.len() as u64 % write_size != 0 {
+            return Err(MtdError::InvalidArgs);
+        }
+        match self.ecc.as_ref() {
+            Some(ecc) => self.write_with_ecc(ecc.as_ref(), self.offset + offset, buf),
+            None => self.driver.write(self.offset + offset, buf),
+        }
+    }
+
+    /// Erases the erase blocks in the range, which must be aligned to the erase blocks.
+    pub fn erase(&self, offset: u64, len: u64) -> Result<(), MtdError> {
+        if self.read_only {
+            return Err(MtdError::ReadOnly);
+        }
+        self.check_range(offset, len)?;
+        let erase_size = self.erase_size() as u64;
+        if offset % erase_size != 0 || len % erase_size != 0 {
+            return Err(MtdError::InvalidArgs);
+        }
+        if len == 0 {
+            return Ok(());
+        }
+        self.driver.erase(self.offset + offset, len)
+    }
+
+    /// Returns whether the erase block at the offset is bad.
+    pub fn is_bad_block(&self, offset: u64) -> bool {
+        offset < self.size && self.driver.is_bad_block(self.offset + offset)
+    }
+
+    fn check_range(&self, offset: u64, len: u64) -> Result<(), MtdError> {
+        if offset.checked_add(len).is_none_or(|end| end > self.size) {
+            return Err(MtdError::InvalidArgs);
+        }
+        Ok(())
+    }
+
+    /// Reads the pages that cover the range with their OOB areas, and corrects them.
+    fn read_with_ecc(
+        &self,
+        ecc: &dyn EccEngine,
+        offset: u64,
+        buf: &mut [u8],
+    ) -> Result<(), MtdError> {
+        let page_size = self.write_size() as u64;
+        let mut page = vec![0u8; page_size as usize];
+        let mut oob = vec![0u8; self.oob_size() as usize];
+        let mut result = Ok(());
+
+        let mut pos = 0;
+        while pos < buf.len() {
+            let addr = offset + pos as u64;
+            let page_start = addr - addr % page_size;
+            self.driver.read(page_start, &mut page)?;
+            self.driver.read_oob(page_start, &mut oob)?;
+
+            for (step, code) in page
+                .chunks_mut(ecc.step_size())
+                .zip(oob.chunks(ecc.ecc_bytes()))
+            {
+                match ecc.correct(step, code) {
+                    Ok(nr_flips) => {
+                        self.ecc_stats
+                            .corrected
+                            .fetch_add(nr_flips as u64, Ordering::Relaxed);
+                    }
+                    Err(err) => {
+                        self.ecc_stats.failed.fetch_add(1, Ordering::Relaxed);
+                        result = Err(err);
+                    }
+                }
+            }
+
+            let start = (addr - page_start) as usize;
+            let len = (page_size as usize - start).min(buf.len() - pos);
+            buf[pos..pos + len].copy_from_slice(&page[start..start + len]);
+            pos += len;
+        }
+        result
+    }
+
+    /// Writes the pages with the codes in their OOB areas.
+    fn write_with_ecc(&self, ecc: &dyn EccEngine, offset: u64, buf: &[u8]) -> Result<(), MtdError> {
+        let page_size = self.write_size() as usize;
+        // The unused bytes of the OOB areas are left erased.
+        let mut oob = vec![0xffu8; self.oob_size() as usize];
+
+        for (index, page) in buf.chunks(page_size).enumerate() {
+            for (step, code) in page
+                .chunks(ecc.step_size())
+                .zip(oob.chunks_mut(ecc.ecc_bytes()))
+            {
+                ecc.calculate(step, code);
+            }
+            let page_start = offset + (index * page_size) as u64;
+            self.driver.write(page_start, page)?;
+            self.driver.write_oob(page_start, &oob)?;
+        }
+        Ok(())
+    }
+}
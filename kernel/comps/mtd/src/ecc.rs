@@ -0,0 +1,113 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The error-correcting codes (ECCs) of the pages.
+
+use crate::MtdError;
+
+/// An ECC engine, which computes and checks the codes of the data in steps.
+///
+/// Each page is divided into the steps of [`EccEngine::step_size`] bytes, and the codes of
+/// the steps are stored in the OOB area of the page one after another.
+pub trait EccEngine: Send + Sync {
+    /// Returns the name of the engine.
+    fn name(&self) -> &str;
+
+    /// Returns the number of the bytes of the data protected by a code.
+    fn step_size(&self) -> usize;
+
+    /// Returns the number of the bytes of a code.
+    fn ecc_bytes(&self) -> usize;
+
+    /// Calculates the code of a step.
+    fn calculate(&self, data: &[u8], ecc: &mut [u8]);
+
+    /// Checks a step with its stored code, correcting the bit flips in place.
+    ///
+    /// This method returns the number of the corrected bit flips, or
+    /// [`MtdError::EccError`] if they cannot be corrected.
+    fn correct(&self, data: &mut [u8], ecc: &[u8]) -> Result<usize, MtdError>;
+}
+
+/// The Hamming code, which corrects a bit flip and detects two bit flips in 256 bytes.
+///
+/// Like the software ECC of Linux, the code consists of the 22 line and column parities of
+/// the step, and is stored in three bytes. The code is inverted, so the code of an erased
+/// step (i.e., all `0xff`) is all `0xff` as well.
+#[derive(Debug, Default)]
+pub struct HammingEcc;
+
+/// The number of the bits of the index of a bit in a step.
+const INDEX_BITS: u32 = 11;
+
+impl HammingEcc {
+    const STEP_SIZE: usize = 256;
+
+    /// Returns the parities, where the bit `2k` is the parity of the bits whose indexes have
+    /// the bit `k`, and the bit `2k + 1` is the parity of the others.
+    fn parities(data: &[u8]) -> u32 {
+        let mut parities = 0u32;
+        let mut columns = 0u8;
+        for (index, byte) in data.iter().enumerate() {
+            columns ^= byte;
+            if byte.count_ones() % 2 == 0 {
+                continue;
+            }
+            // The bits 3..11 of the indexes of the bits are the indexes of the bytes.
+            for k in 0..8 {
+                let is_set = (index >> k) & 1 != 0;
+                parities ^= 1 << (2 * (k + 3) + if is_set { 0 } else { 1 });
+            }
+        }
+        // The bits 0..3 of the indexes of the bits are the positions in the bytes.
+        for (k, mask) in [0xaau8, 0xcc, 0xf0].into_iter().enumerate() {
+            parities |= ((columns & mask).count_ones() % 2) << (2 * k);
+            parities |= ((columns & !mask).count_ones() % 2) << (2 * k + 1);
+        }
+        parities
+    }
+}
+
+impl EccEngine for HammingEcc {
+    fn name(&self) -> &str {
+        "hamming"
+    }
+
+    fn step_size(&self) -> usize {
+        Self::STEP_SIZE
+    }
+
+    fn ecc_bytes(&self) -> usize {
+        3
+    }
+
+    fn calculate(&self, data: &[u8], ecc: &mut [u8]) {
+        let code = !Self::parities(data);
+        ecc[..3].copy_from_slice(&code.to_le_bytes()[..3]);
+    }
+
+    fn correct(&self, data: &mut [u8], ecc: &[u8]) -> Result<usize, MtdError> {
+        let mask = (1u32 << (2 * INDEX_BITS)) - 1;
+        let stored = !u32::from_le_bytes([ecc[0], ecc[1], ecc[2], 0xff]);
+        let syndrome = (stored ^ Self::parities(data)) & mask;
+        if syndrome == 0 {
+            return Ok(0);
+        }
+        // A bit flip in the code itself.
+        if syndrome.count_ones() == 1 {
+            return Ok(1);
+        }
+
+        // A bit flip in the data flips exactly one parity of each pair.
+        let is_single_flip =
+            (0..INDEX_BITS).all(|k| ((syndrome >> (2 * k)) & 0b11).count_ones() == 1);
+        if !is_single_flip {
+            return Err(MtdError::EccError);
+        }
+        let index = (0..INDEX_BITS).fold(0usize, |index, k| {
+            index | ((((syndrome >> (2 * k)) & 1) as usize) << k)
+        });
+        let byte = data.get_mut(index / 8).ok_or(MtdError::EccError)?;
+        *byte ^= 1 << (index % 8);
+        Ok(1)
+    }
+}
@@ -0,0 +1,198 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The memory technology device (MTD) subsystem of Asterinas.
+//!
+//! An MTD is a raw flash, which differs from a block device in that its erase blocks must be
+//! erased before they are written. Each flash is driven by an [`MtdDriver`] and registered
+//! with its partitions, each of which becomes an [`MtdDevice`] named `mtdN` like Linux. The
+//! partitions are usually described by the device tree (see [`parse_fdt_partitions`]).
+//!
+//! The flashes with the out-of-band (OOB) areas (e.g., the NAND flashes) can be registered
+//! with an [`EccEngine`], which protects each page by the error-correcting codes stored in
+//! its OOB area. A Hamming code like the software ECC of Linux is provided by [`HammingEcc`].
+//!
+//! The following drivers are provided:
+//!
+//! - `ram`: a flash emulated by memory, like the `mtdram` of Linux. It is created if the size
+//!   (in KiB) is given by `mtdram.total_size=` in the kernel command line, and the size (in
+//!   KiB) of the erase blocks can be given by `mtdram.erase_size=`.
+//!
+//! Other drivers (e.g., the SPI-NOR flashes of `aster-spi`) register their flashes by
+//! [`register_device`].
+
+#![no_std]
+#![deny(unsafe_code)]
+
+extern crate alloc;
+
+mod device;
+mod ecc;
+mod partition;
+mod ram;
+
+use alloc::{sync::Arc, vec::Vec};
+
+use component::{init_component, ComponentInitError};
+use log::info;
+use ostd::sync::{LocalIrqDisabled, SpinLock};
+
+#[cfg(target_arch = "riscv64")]
+pub use self::partition::parse_fdt_partitions;
+pub use self::{
+    device::{EccStats, MtdDevice},
+    ecc::{EccEngine, HammingEcc},
+    partition::MtdPartitionInfo,
+    ram::MtdRam,
+};
+
+static DEVICES: SpinLock<Vec<Arc<MtdDevice>>, LocalIrqDisabled> = SpinLock::new(Vec::new());
+
+#[init_component]
+fn mtd_component_init() -> Result<(), ComponentInitError> {
+    ram::init();
+    Ok(())
+}
+
+/// The type of a flash, whose values are the same as Linux.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(u8)]
+pub enum MtdType {
+    /// A flash emulated by memory
+    Ram = 2,
+    /// A NOR flash
+    NorFlash = 3,
+    /// A NAND flash
+    NandFlash = 4,
+}
+
+/// The errors of the flash operations.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MtdError {
+    /// The range is out of the device, or is not aligned as required.
+    InvalidArgs,
+    /// The device is read-only.
+    ReadOnly,
+    /// The operation is not supported by the flash.
+    NotSupported,
+    /// The erase block is bad.
+    BadBlock,
+    /// The data has more bit flips than the ECC can correct.
+    EccError,
+    /// The flash does not complete the operation in time.
+    Timeout,
+    /// The flash or its controller reports another error.
+    IoError,
+}
+
+/// A flash driver.
+///
+/// The ranges of the operations have been checked to be within the flash, and the ranges of
+/// the erases are aligned to the erase blocks. The operations may sleep.
+pub trait MtdDriver: Send + Sync {
+    /// Returns the name of the flash.
+    fn name(&self) -> &str;
+
+    /// Returns the type of the flash.
+    fn type_(&self) -> MtdType;
+
+    /// Returns the size (in bytes) of the flash.
+    fn size(&self) -> u64;
+
+    /// Returns the size (in bytes) of the erase blocks.
+    fn erase_size(&self) -> u32;
+
+    /// Returns the minimal size (in bytes) of the writes, i.e., the size of the pages.
+    fn write_size(&self) -> u32 {
+        1
+    }
+
+    /// Returns the size (in bytes) of the OOB area of each page.
+    fn oob_size(&self) -> u32 {
+        0
+    }
+
+    /// Reads the bytes at the offset.
+    fn read(&self, offset: u64, buf: &mut [u8]) -> Result<(), MtdError>;
+
+    /// Writes the bytes at the offset, which must have been erased.
+    fn write(&self, offset: u64, buf: &[u8]) -> Result<(), MtdError>;
+
+    /// Erases the erase blocks in the range.
+    fn erase(&self, offset: u64, len: u64) -> Result<(), MtdError>;
+
+    /// Reads the OOB area of the page at the offset.
+    fn read_oob(&self, _offset: u64, _oob: &mut [u8]) -> Result<(), MtdError> {
+        Err(MtdError::NotSupported)
+    }
+
+    /// Writes the OOB area of the page at the offset, which must have been erased.
+    fn write_oob(&self, _offset: u64, _oob: &[u8]) -> Result<(), MtdError> {
+        Err(MtdError::NotSupported)
+    }
+
+    /// Returns whether the erase block at the offset is bad.
+    fn is_bad_block(&self, _offset: u64) -> bool {
+        false
+    }
+}
+
+/// Registers a flash with its partitions, returning the registered devices.
+///
+/// A flash without partitions is registered as a single device. The partitions that are out
+/// of the flash are ignored, and those that are not aligned to the erase blocks are
+/// read-only. The ECC engine is only used if the codes of a page fit in its OOB area.
+pub fn register_device(
+    driver: Arc<dyn MtdDriver>,
+    ecc: Option<Arc<dyn EccEngine>>,
+    partitions: Vec<MtdPartitionInfo>,
+) -> Vec<Arc<MtdDevice>> {
+    let ecc = ecc.filter(|ecc| {
+        let page_size = driver.write_size() as usize;
+        let nr_steps = page_size / ecc.step_size();
+        page_size % ecc.step_size() == 0
+            && nr_steps * ecc.ecc_bytes() <= driver.oob_size() as usize
+            && nr_steps > 0
+    });
+    let partitions = partition::validate(driver.as_ref(), partitions);
+
+    let mut devices = DEVICES.lock();
+    let mut registered = Vec::with_capacity(partitions.len());
+    for info in partitions {
+        let device = Arc::new(MtdDevice::new(
+            devices.len(),
+            driver.clone(),
+            ecc.clone(),
+            info,
+        ));
+        info!(
+            "[MTD]: registered mtd{} ({}) at {:#x}-{:#x} of {}",
+            device.index(),
+            device.name(),
+            device.offset(),
+            device.offset() + device.size(),
+            driver.name()
+        );
+        devices.push(device.clone());
+        registered.push(device);
+    }
+    registered
+}
+
+/// Returns the device with the index.
+pub fn get_device(index: usize) -> Option<Arc<MtdDevice>> {
+    DEVICES.lock().get(index).cloned()
+}
+
+/// Returns all the registered devices.
+pub fn all_devices() -> Vec<Arc<MtdDevice>> {
+    DEVICES.lock().clone()
+}
+
+/// Returns the device with the name, i.e., the label of the partition.
+pub fn find_device(name: &str) -> Option<Arc<MtdDevice>> {
+    DEVICES
+        .lock()
+        .iter()
+        .find(|device| device.name() == name)
+        .cloned()
+}
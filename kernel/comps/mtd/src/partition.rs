@@ -0,0 +1,103 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The partitions of the flashes.
+//!
+//! Like Linux, the partitions are described by the `fixed-partitions` node under the node of
+//! a flash, or by the children of the node of the flash in the legacy binding.
+//!
+//! Reference: <https://www.kernel.org/doc/Documentation/devicetree/bindings/mtd/partitions/fixed-partitions.yaml>
+
+use alloc::{string::String, vec, vec::Vec};
+
+use log::warn;
+
+use crate::MtdDriver;
+
+/// A partition of a flash.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct MtdPartitionInfo {
+    /// The name (i.e., the label) of the partition
+    pub name: String,
+    /// The offset (in bytes) of the partition in the flash
+    pub offset: u64,
+    /// The size (in bytes) of the partition
+    pub size: u64,
+    /// Whether the partition is read-only
+    pub read_only: bool,
+}
+
+/// Checks the partitions of the flash, returning the valid ones.
+///
+/// A flash without partitions has a single partition that covers the flash.
+pub(crate) fn validate(
+    driver: &dyn MtdDriver,
+    partitions: Vec<MtdPartitionInfo>,
+) -> Vec<MtdPartitionInfo> {
+    if partitions.is_empty() {
+        return vec![MtdPartitionInfo {
+            name: String::from(driver.name()),
+            offset: 0,
+            size: driver.size(),
+            read_only: false,
+        }];
+    }
+
+    let erase_size = driver.erase_size() as u64;
+    partitions
+        .into_iter()
+        .filter_map(|mut info| {
+            if info.size == 0
+                || info
+                    .offset
+                    .checked_add(info.size)
+                    .is_none_or(|end| end > driver.size())
+            {
+                warn!(
+                    "[MTD]: {}: the partition {} is out of the flash",
+                    driver.name(),
+                    info.name
+                );
+                return None;
+            }
+            if info.offset % erase_size != 0 || info.size % erase_size != 0 {
+                warn!(
+                    "[MTD]: {}: the partition {} is not aligned to the erase blocks, forcing read-only",
+                    driver.name(),
+                    info.name
+                );
+                info.read_only = true;
+            }
+            Some(info)
+        })
+        .collect()
+}
+
+/// Returns the partitions that are described by the node of the flash in the device tree.
+#[cfg(target_arch = "riscv64")]
+pub fn parse_fdt_partitions(node: &fdt::node::FdtNode) -> Vec<MtdPartitionInfo> {
+    let partitions_node = node.children().find(|child| {
+        child
+            .compatible()
+            .is_some_and(|compatible| compatible.all().any(|c| c == "fixed-partitions"))
+    });
+
+    partitions_node
+        .as_ref()
+        .unwrap_or(node)
+        .children()
+        .filter(|child| child.compatible().is_none())
+        .filter_map(|child| {
+            let reg = child.reg()?.next()?;
+            let name = child
+                .property("label")
+                .and_then(|prop| prop.as_str())
+                .unwrap_or_else(|| child.name.split('@').next().unwrap());
+            Some(MtdPartitionInfo {
+                name: String::from(name),
+                offset: reg.starting_address as u64,
+                size: reg.size? as u64,
+                read_only: child.property("read-only").is_some(),
+            })
+        })
+        .collect()
+}
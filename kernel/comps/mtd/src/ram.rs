@@ -0,0 +1,102 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The flash emulated by memory, like the `mtdram` of Linux.
+//!
+//! The flash behaves like a NOR flash, i.e., the erased bytes are `0xff`, and a write can
+//! only clear the bits. It is useful for testing the flash filesystems without a flash.
+
+use alloc::{sync::Arc, vec, vec::Vec};
+
+use log::warn;
+use ostd::sync::Mutex;
+
+use crate::{MtdDriver, MtdError, MtdType};
+
+/// The default size (in KiB) of the erase blocks, which is the same as Linux.
+const DEFAULT_ERASE_SIZE_KIB: u32 = 128;
+
+/// A flash emulated by memory.
+pub struct MtdRam {
+    data: Mutex<Vec<u8>>,
+    erase_size: u32,
+}
+
+impl MtdRam {
+    /// Creates an erased flash.
+    ///
+    /// The size must be a multiple of the size of the erase blocks.
+    pub fn new(size: usize, erase_size: u32) -> Option<Self> {
+        if erase_size == 0 || size == 0 || size % erase_size as usize != 0 {
+            return None;
+        }
+        Some(Self {
+            data: Mutex::new(vec![0xff; size]),
+            erase_size,
+        })
+    }
+}
+
+impl MtdDriver for MtdRam {
+    fn name(&self) -> &str {
+        "mtdram test device"
+    }
+
+    fn type_(&self) -> MtdType {
+        MtdType::Ram
+    }
+
+    fn size(&self) -> u64 {
+        self.data.lock().len() as u64
+    }
+
+    fn erase_size(&self) -> u32 {
+        self.erase_size
+    }
+
+    fn read(&self, offset: u64, buf: &mut [u8]) -> Result<(), MtdError> {
+        let offset = offset as usize;
+        buf.copy_from_slice(&self.data.lock()[offset..offset + buf.len()]);
+        Ok(())
+    }
+
+    fn write(&self, offset: u64, buf: &[u8]) -> Result<(), MtdError> {
+        let offset = offset as usize;
+        let mut data = self.data.lock();
+        for (byte, new_byte) in data[offset..offset + buf.len()].iter_mut().zip(buf) {
+            *byte &= new_byte;
+        }
+        Ok(())
+    }
+
+    fn erase(&self, offset: u64, len: u64) -> Result<(), MtdError> {
+        let offset = offset as usize;
+        self.data.lock()[offset..offset + len as usize].fill(0xff);
+        Ok(())
+    }
+}
+
+/// Creates the flash if its size is given in the kernel command line.
+pub(crate) fn init() {
+    let mut total_size_kib = None;
+    let mut erase_size_kib = DEFAULT_ERASE_SIZE_KIB;
+    for arg in ostd::boot::boot_info().kernel_cmdline.split(' ') {
+        if let Some(size) = arg.strip_prefix("mtdram.total_size=") {
+            total_size_kib = size.parse::<usize>().ok();
+        } else if let Some(size) = arg.strip_prefix("mtdram.erase_size=") {
+            erase_size_kib = size.parse().unwrap_or(DEFAULT_ERASE_SIZE_KIB);
+        }
+    }
+    let Some(total_size_kib) = total_size_kib else {
+        return;
+    };
+
+    match MtdRam::new(total_size_kib * 1024, erase_size_kib * 1024) {
+        Some(ram) => {
+            crate::register_device(Arc::new(ram), None, Vec::new());
+        }
+        None => warn!(
+            "[MTD]: invalid mtdram size {} KiB with erase blocks of {} KiB",
+            total_size_kib, erase_size_kib
+        ),
+    }
+}
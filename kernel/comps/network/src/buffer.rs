@@ -129,6 +129,14 @@ impl RxBuffer {
     pub const fn buf_len(&self) -> usize {
         self.segment.size()
     }
+
+    /// Synchronizes the buffer before the device writes to it.
+    ///
+    /// On non-coherent platforms, this discards the cached contents of the buffer, so that
+    /// they are never written back over the data written by the device.
+    pub fn sync_for_device(&self) {
+        self.segment.sync(0..self.buf_len()).unwrap();
+    }
 }
 
 impl HasDaddr for RxBuffer {
@@ -0,0 +1,80 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The registered SPI controllers.
+
+use alloc::sync::Arc;
+
+use ostd::sync::Mutex;
+
+use crate::{SpiControllerDriver, SpiDevice, SpiError, SpiTransfer};
+
+/// A registered SPI controller.
+///
+/// The transfers may sleep, so they must not be started in the atomic context.
+pub struct SpiController {
+    index: usize,
+    driver: Arc<dyn SpiControllerDriver>,
+    /// The lock of the bus, which serializes the messages
+    bus_lock: Mutex<()>,
+}
+
+impl SpiController {
+    pub(crate) fn new(index: usize, driver: Arc<dyn SpiControllerDriver>) -> Self {
+        Self {
+            index,
+            driver,
+            bus_lock: Mutex::new(()),
+        }
+    }
+
+    /// Returns the index of the controller.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the name of the controller.
+    pub fn name(&self) -> &str {
+        self.driver.name()
+    }
+
+    /// Returns the number of the chip selects.
+    pub fn num_chipselect(&self) -> u32 {
+        self.driver.num_chipselect()
+    }
+
+    /// Returns the maximum number of the data lines.
+    pub fn max_bus_width(&self) -> u8 {
+        self.driver.max_bus_width()
+    }
+
+    /// Transfers a message to the device, which must be on this controller.
+    pub(crate) fn transfer(
+        &self,
+        device: &SpiDevice,
+        transfers: &mut [SpiTransfer],
+    ) -> Result<(), SpiError> {
+        if device.chip_select() >= self.num_chipselect() {
+            return Err(SpiError::InvalidArgs);
+        }
+        for transfer in transfers.iter() {
+            if ![1, 2, 4].contains(&transfer.bus_width) {
+                return Err(SpiError::InvalidArgs);
+            }
+            if transfer.bus_width > self.max_bus_width() {
+                return Err(SpiError::NotSupported);
+            }
+            match (transfer.tx_buf, transfer.rx_buf.as_ref()) {
+                (Some(tx_buf), Some(rx_buf))
+                    if transfer.bus_width > 1 || tx_buf.len() != rx_buf.len() =>
+                {
+                    return Err(SpiError::InvalidArgs);
+                }
+                (None, None) => return Err(SpiError::InvalidArgs),
+                _ => (),
+            }
+        }
+
+        let _guard = self.bus_lock.lock();
+        self.driver.transfer(device, transfers)
+    }
+}
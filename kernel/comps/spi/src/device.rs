@@ -0,0 +1,169 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The SPI devices on the controllers.
+
+use alloc::{string::String, sync::Arc, vec::Vec};
+
+use crate::{SpiController, SpiError, SpiMode, SpiTransfer};
+
+/// The default maximum frequency (in Hz) of the devices.
+#[cfg(target_arch = "riscv64")]
+const DEFAULT_MAX_SPEED_HZ: u32 = 1_000_000;
+
+/// An SPI device on a controller, which is selected by its chip select.
+pub struct SpiDevice {
+    controller: Arc<SpiController>,
+    chip_select: u32,
+    mode: SpiMode,
+    max_speed_hz: u32,
+    tx_bus_width: u8,
+    rx_bus_width: u8,
+    name: String,
+    compatibles: Vec<String>,
+    #[cfg(target_arch = "riscv64")]
+    node: fdt::node::FdtNode<'static, 'static>,
+}
+
+impl SpiDevice {
+    /// Returns the controller of the device.
+    pub fn controller(&self) -> &Arc<SpiController> {
+        &self.controller
+    }
+
+    /// Returns the chip select of the device.
+    pub fn chip_select(&self) -> u32 {
+        self.chip_select
+    }
+
+    /// Returns the mode of the device.
+    pub fn mode(&self) -> SpiMode {
+        self.mode
+    }
+
+    /// Returns the maximum frequency (in Hz) of the clock.
+    pub fn max_speed_hz(&self) -> u32 {
+        self.max_speed_hz
+    }
+
+    /// Returns the number of the data lines to send the data, which is wired on the board.
+    pub fn tx_bus_width(&self) -> u8 {
+        self.tx_bus_width
+    }
+
+    /// Returns the number of the data lines to receive the data, which is wired on the board.
+    pub fn rx_bus_width(&self) -> u8 {
+        self.rx_bus_width
+    }
+
+    /// Returns the name of the device, which is its first compatible string without the
+    /// vendor, like Linux.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns whether the device is compatible with any of the compatible strings.
+    pub fn is_compatible_with(&self, compatibles: &[&str]) -> bool {
+        self.compatibles
+            .iter()
+            .any(|compatible| compatibles.contains(&compatible.as_str()))
+    }
+
+    /// Returns the node of the device in the device tree.
+    #[cfg(target_arch = "riscv64")]
+    pub fn fdt_node(&self) -> &fdt::node::FdtNode<'static, 'static> {
+        &self.node
+    }
+
+    /// Transfers a message, during which the device is selected.
+    pub fn transfer(&self, transfers: &mut [SpiTransfer]) -> Result<(), SpiError> {
+        self.controller.transfer(self, transfers)
+    }
+
+    /// Sends the bytes.
+    pub fn write(&self, buf: &[u8]) -> Result<(), SpiError> {
+        self.transfer(&mut [SpiTransfer::write(buf)])
+    }
+
+    /// Receives the bytes.
+    pub fn read(&self, buf: &mut [u8]) -> Result<(), SpiError> {
+        self.transfer(&mut [SpiTransfer::read(buf)])
+    }
+
+    /// Sends the bytes, and then receives the bytes in the same message.
+    pub fn write_then_read(&self, tx_buf: &[u8], rx_buf: &mut [u8]) -> Result<(), SpiError> {
+        self.transfer(&mut [SpiTransfer::write(tx_buf), SpiTransfer::read(rx_buf)])
+    }
+}
+
+/// Returns the devices that are described by the enabled children of the controller's node.
+#[cfg(target_arch = "riscv64")]
+pub(crate) fn probe_devices(
+    controller: &Arc<SpiController>,
+    node: &fdt::node::FdtNode<'static, 'static>,
+) -> Vec<SpiDevice> {
+    use alloc::string::ToString;
+
+    node.children()
+        .filter(|child| {
+            child
+                .property("status")
+                .and_then(|prop| prop.as_str())
+                .is_none_or(|status| status == "okay" || status == "ok")
+        })
+        .filter_map(|child| {
+            let reg = child.property("reg")?.value.get(0..4)?;
+            let chip_select = u32::from_be_bytes(reg.try_into().unwrap());
+
+            let compatibles: Vec<String> = child
+                .compatible()?
+                .all()
+                .map(|compatible| compatible.to_string())
+                .collect();
+            let name = compatibles
+                .first()
+                .map(|compatible| {
+                    compatible
+                        .split_once(',')
+                        .map_or(compatible.as_str(), |(_, name)| name)
+                })?
+                .to_string();
+
+            let mut mode = SpiMode::empty();
+            for (property, flag) in [
+                ("spi-cpha", SpiMode::CPHA),
+                ("spi-cpol", SpiMode::CPOL),
+                ("spi-cs-high", SpiMode::CS_HIGH),
+                ("spi-lsb-first", SpiMode::LSB_FIRST),
+            ] {
+                mode.set(flag, child.property(property).is_some());
+            }
+            let read_u32 = |name: &str| {
+                child
+                    .property(name)
+                    .and_then(|prop| prop.as_usize())
+                    .map(|value| value as u32)
+            };
+            // The wider buses are only used if they are wired and supported by the controller.
+            let bus_width = |name: &str| {
+                let width = read_u32(name).unwrap_or(1) as u8;
+                if [1, 2, 4].contains(&width) {
+                    width.min(controller.max_bus_width())
+                } else {
+                    1
+                }
+            };
+
+            Some(SpiDevice {
+                controller: controller.clone(),
+                chip_select,
+                mode,
+                max_speed_hz: read_u32("spi-max-frequency").unwrap_or(DEFAULT_MAX_SPEED_HZ),
+                tx_bus_width: bus_width("spi-tx-bus-width"),
+                rx_bus_width: bus_width("spi-rx-bus-width"),
+                name,
+                compatibles,
+                node: child,
+            })
+        })
+        .collect()
+}
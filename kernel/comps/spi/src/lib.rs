@@ -0,0 +1,239 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The SPI subsystem of Asterinas.
+//!
+//! Each SPI controller is driven by an [`SpiControllerDriver`] and registered as an
+//! [`SpiController`], which serializes the messages on the bus. The devices on the bus are
+//! described by the children of the node of the controller in the device tree, which become
+//! the [`SpiDevice`]s of the controller. The drivers of the devices find their devices by
+//! [`find_devices`].
+//!
+//! The serial NOR flashes (`jedec,spi-nor`) are driven by this crate as well. Each flash is
+//! registered to `aster-mtd` with the partitions described by the device tree.
+//!
+//! The following controller drivers are provided:
+//!
+//! - `sifive`: the SiFive SPI/QSPI controller, which is used by the SiFive SoCs.
+//!
+//! The controllers are polled, and are discovered through the device tree.
+
+#![no_std]
+#![deny(unsafe_code)]
+
+extern crate alloc;
+
+mod controller;
+mod device;
+mod nor;
+#[cfg(target_arch = "riscv64")]
+mod sifive;
+
+use alloc::{sync::Arc, vec::Vec};
+
+use bitflags::bitflags;
+use component::{init_component, ComponentInitError};
+use log::info;
+use ostd::sync::{LocalIrqDisabled, SpinLock};
+
+pub use self::{controller::SpiController, device::SpiDevice, nor::SpiNor};
+
+static CONTROLLERS: SpinLock<Vec<Arc<SpiController>>, LocalIrqDisabled> = SpinLock::new(Vec::new());
+static DEVICES: SpinLock<Vec<Arc<SpiDevice>>, LocalIrqDisabled> = SpinLock::new(Vec::new());
+
+#[init_component]
+fn spi_component_init() -> Result<(), ComponentInitError> {
+    #[cfg(target_arch = "riscv64")]
+    {
+        sifive::init();
+        nor::init();
+    }
+    Ok(())
+}
+
+bitflags! {
+    /// The mode of an SPI device, whose bits are the same as Linux.
+    pub struct SpiMode: u32 {
+        /// The data is sampled on the trailing edge of the clock.
+        const CPHA = 1 << 0;
+        /// The clock is high when idle.
+        const CPOL = 1 << 1;
+        /// The chip select is active high.
+        const CS_HIGH = 1 << 2;
+        /// The least significant bit is sent first.
+        const LSB_FIRST = 1 << 3;
+    }
+}
+
+/// A transfer of an SPI message.
+///
+/// The bytes are sent from `tx_buf` (or zeros if it is `None`), and the received bytes are
+/// stored to `rx_buf` (or discarded if it is `None`). The transfers with multiple data lines
+/// are half-duplex, so they cannot have both buffers.
+#[derive(Debug)]
+pub struct SpiTransfer<'a> {
+    /// The bytes to send
+    pub tx_buf: Option<&'a [u8]>,
+    /// The buffer to receive into
+    pub rx_buf: Option<&'a mut [u8]>,
+    /// The number of the data lines, which is 1, 2, or 4
+    pub bus_width: u8,
+}
+
+impl<'a> SpiTransfer<'a> {
+    /// Creates a transfer that sends the bytes.
+    pub fn write(buf: &'a [u8]) -> Self {
+        Self {
+            tx_buf: Some(buf),
+            rx_buf: None,
+            bus_width: 1,
+        }
+    }
+
+    /// Creates a transfer that receives the bytes.
+    pub fn read(buf: &'a mut [u8]) -> Self {
+        Self {
+            tx_buf: None,
+            rx_buf: Some(buf),
+            bus_width: 1,
+        }
+    }
+
+    /// Creates a full-duplex transfer.
+    pub fn duplex(tx_buf: &'a [u8], rx_buf: &'a mut [u8]) -> Self {
+        Self {
+            tx_buf: Some(tx_buf),
+            rx_buf: Some(rx_buf),
+            bus_width: 1,
+        }
+    }
+
+    /// Sets the number of the data lines.
+    pub fn with_bus_width(mut self, bus_width: u8) -> Self {
+        self.bus_width = bus_width;
+        self
+    }
+
+    /// Returns the length of the transfer.
+    pub fn len(&self) -> usize {
+        self.tx_buf
+            .map(|buf| buf.len())
+            .or(self.rx_buf.as_ref().map(|buf| buf.len()))
+            .unwrap_or(0)
+    }
+
+    /// Returns whether the transfer is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// The errors of SPI transfers.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SpiError {
+    /// The transfers are invalid.
+    InvalidArgs,
+    /// The controller does not respond in time.
+    Timeout,
+    /// The transfers are not supported by the controller.
+    NotSupported,
+    /// The controller reports another error.
+    IoError,
+}
+
+/// An SPI controller driver.
+///
+/// The transfers of a message are sent while the chip select of the device is asserted, and
+/// the chip select is deasserted after the message. The messages are serialized by the
+/// controller.
+pub trait SpiControllerDriver: Send + Sync {
+    /// Returns the name of the controller.
+    fn name(&self) -> &str;
+
+    /// Returns the number of the chip selects.
+    fn num_chipselect(&self) -> u32;
+
+    /// Returns the maximum number of the data lines.
+    fn max_bus_width(&self) -> u8 {
+        1
+    }
+
+    /// Transfers a message to the device.
+    fn transfer(&self, device: &SpiDevice, transfers: &mut [SpiTransfer]) -> Result<(), SpiError>;
+}
+
+/// Registers an SPI controller driver, returning the registered controller.
+pub fn register_controller(driver: Arc<dyn SpiControllerDriver>) -> Arc<SpiController> {
+    let mut controllers = CONTROLLERS.lock();
+    let controller = Arc::new(SpiController::new(controllers.len(), driver));
+    info!(
+        "[SPI]: registered spi{} ({})",
+        controller.index(),
+        controller.name()
+    );
+    controllers.push(controller.clone());
+    controller
+}
+
+/// Registers an SPI controller driver with the node of the controller in the device tree,
+/// returning the registered controller.
+///
+/// The children of the node are registered as the devices of the controller.
+#[cfg(target_arch = "riscv64")]
+pub fn register_fdt_controller(
+    driver: Arc<dyn SpiControllerDriver>,
+    node: &fdt::node::FdtNode<'static, 'static>,
+) -> Arc<SpiController> {
+    let controller = register_controller(driver);
+    for device in device::probe_devices(&controller, node) {
+        info!(
+            "[SPI]: found device {} at spi{}.{}",
+            device.name(),
+            controller.index(),
+            device.chip_select()
+        );
+        DEVICES.lock().push(Arc::new(device));
+    }
+    controller
+}
+
+/// Returns the controller with the index.
+pub fn get_controller(index: usize) -> Option<Arc<SpiController>> {
+    CONTROLLERS.lock().get(index).cloned()
+}
+
+/// Returns all the registered controllers.
+pub fn all_controllers() -> Vec<Arc<SpiController>> {
+    CONTROLLERS.lock().clone()
+}
+
+/// Returns all the devices on the controllers.
+pub fn all_devices() -> Vec<Arc<SpiDevice>> {
+    DEVICES.lock().clone()
+}
+
+/// Returns the devices that are compatible with any of the compatible strings.
+pub fn find_devices(compatibles: &[&str]) -> Vec<Arc<SpiDevice>> {
+    DEVICES
+        .lock()
+        .iter()
+        .filter(|device| device.is_compatible_with(compatibles))
+        .cloned()
+        .collect()
+}
+
+/// Polls `cond` until it returns `true`, or until `timeout_us` microseconds have elapsed.
+#[cfg(target_arch = "riscv64")]
+fn poll_until(timeout_us: u64, mut cond: impl FnMut() -> bool) -> bool {
+    let freq = ostd::arch::tsc_freq().max(1);
+    let deadline = ostd::arch::read_tsc() + timeout_us * freq / 1_000_000;
+    loop {
+        if cond() {
+            return true;
+        }
+        if ostd::arch::read_tsc() > deadline {
+            // Check once more in case that we were preempted for a long time.
+            return cond();
+        }
+        core::hint::spin_loop();
+    }
+}
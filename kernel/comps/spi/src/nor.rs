@@ -0,0 +1,386 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The driver of the serial NOR flashes, i.e., the `jedec,spi-nor` devices.
+//!
+//! The flashes are identified by their JEDEC IDs. The known flashes are described by a
+//! table, and the unknown flashes are assumed to follow the common conventions, i.e., the
+//! capacity is encoded in the third byte of the ID, and the 64 KiB blocks can be erased.
+//! The flashes larger than 16 MiB are accessed with 4-byte addresses. The fast reads with
+//! two or four data lines are used if the data lines are wired on the board.
+//!
+//! Reference: <https://www.jedec.org/standards-documents/docs/jesd216b>
+
+use alloc::{format, string::String, sync::Arc};
+
+use aster_mtd::{MtdDriver, MtdError, MtdType};
+use ostd::sync::Mutex;
+
+use crate::{SpiDevice, SpiError, SpiTransfer};
+
+#[cfg(target_arch = "riscv64")]
+const SPI_NOR_COMPATIBLES: &[&str] = &["jedec,spi-nor"];
+
+const SPINOR_OP_WREN: u8 = 0x06;
+const SPINOR_OP_RDSR: u8 = 0x05;
+const SPINOR_OP_WRSR: u8 = 0x01;
+const SPINOR_OP_RDSR2: u8 = 0x35;
+const SPINOR_OP_WRSR2: u8 = 0x31;
+const SPINOR_OP_RDID: u8 = 0x9f;
+const SPINOR_OP_EN4B: u8 = 0xb7;
+
+const SPINOR_OP_READ: u8 = 0x03;
+const SPINOR_OP_READ_1_1_2: u8 = 0x3b;
+const SPINOR_OP_READ_1_1_4: u8 = 0x6b;
+const SPINOR_OP_PP: u8 = 0x02;
+const SPINOR_OP_BE_4K: u8 = 0x20;
+const SPINOR_OP_SE: u8 = 0xd8;
+
+const SPINOR_OP_READ_4B: u8 = 0x13;
+const SPINOR_OP_READ_1_1_2_4B: u8 = 0x3c;
+const SPINOR_OP_READ_1_1_4_4B: u8 = 0x6c;
+const SPINOR_OP_PP_4B: u8 = 0x12;
+const SPINOR_OP_BE_4K_4B: u8 = 0x21;
+const SPINOR_OP_SE_4B: u8 = 0xdc;
+
+/// The write in progress bit of the status register.
+const SR_WIP: u8 = 1 << 0;
+
+/// The size of the pages, which are programmed at once.
+const PAGE_SIZE: usize = 256;
+/// The size of the 64 KiB blocks.
+const SECTOR_SIZE: u32 = 64 * 1024;
+
+/// The time (in microseconds) to wait for a page to be programmed.
+const PROGRAM_TIMEOUT_US: u64 = 10_000;
+/// The time (in microseconds) to wait for a block to be erased.
+const ERASE_TIMEOUT_US: u64 = 5_000_000;
+
+impl From<SpiError> for MtdError {
+    fn from(err: SpiError) -> Self {
+        match err {
+            SpiError::InvalidArgs => MtdError::InvalidArgs,
+            SpiError::Timeout => MtdError::Timeout,
+            SpiError::NotSupported => MtdError::NotSupported,
+            SpiError::IoError => MtdError::IoError,
+        }
+    }
+}
+
+/// How the quad mode is enabled in the status registers.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum QuadEnable {
+    /// The quad mode is always enabled, or not supported.
+    None,
+    /// The bit 6 of the status register 1 (e.g., ISSI and Macronix).
+    Sr1Bit6,
+    /// The bit 1 of the status register 2 (e.g., Winbond and GigaDevice).
+    Sr2Bit1,
+}
+
+/// A known flash.
+struct FlashInfo {
+    name: &'static str,
+    id: [u8; 3],
+    /// Whether the 4 KiB sectors can be erased
+    has_4k_sectors: bool,
+    /// Whether the opcodes with 4-byte addresses are supported
+    has_4b_opcodes: bool,
+    quad_enable: QuadEnable,
+}
+
+const fn flash_info(
+    name: &'static str,
+    id: [u8; 3],
+    has_4b_opcodes: bool,
+    quad_enable: QuadEnable,
+) -> FlashInfo {
+    FlashInfo {
+        name,
+        id,
+        has_4k_sectors: true,
+        has_4b_opcodes,
+        quad_enable,
+    }
+}
+
+/// The known flashes, which are used by the RISC-V boards.
+///
+/// The capacity is encoded in the third byte of the ID.
+const FLASH_INFOS: &[FlashInfo] = &[
+    flash_info("gd25lq128", [0xc8, 0x60, 0x18], false, QuadEnable::Sr2Bit1),
+    flash_info("gd25q128", [0xc8, 0x40, 0x18], false, QuadEnable::Sr2Bit1),
+    flash_info("is25wp128", [0x9d, 0x70, 0x18], false, QuadEnable::Sr1Bit6),
+    flash_info("is25wp256", [0x9d, 0x70, 0x19], true, QuadEnable::Sr1Bit6),
+    flash_info(
+        "mx25l12805d",
+        [0xc2, 0x20, 0x18],
+        false,
+        QuadEnable::Sr1Bit6,
+    ),
+    flash_info(
+        "mx25l25635e",
+        [0xc2, 0x20, 0x19],
+        false,
+        QuadEnable::Sr1Bit6,
+    ),
+    flash_info("w25q128", [0xef, 0x40, 0x18], false, QuadEnable::Sr2Bit1),
+    flash_info("w25q256", [0xef, 0x40, 0x19], true, QuadEnable::Sr2Bit1),
+];
+
+/// A serial NOR flash.
+///
+/// The bits of a flash can only be programmed from one to zero, so the blocks must be erased
+/// (i.e., filled with `0xff`) before they are written.
+pub struct SpiNor {
+    device: Arc<SpiDevice>,
+    name: String,
+    size: u64,
+    erase_size: u32,
+    erase_opcode: u8,
+    /// The number of the bytes of the addresses, which is 3 or 4
+    addr_width: u8,
+    read_opcode: u8,
+    /// The number of the data lines to read
+    read_width: u8,
+    program_opcode: u8,
+    /// The lock that serializes the operations, each of which consists of several messages
+    lock: Mutex<()>,
+}
+
+impl SpiNor {
+    /// Identifies the flash on the device, and prepares it for the operations.
+    pub fn probe(device: Arc<SpiDevice>) -> Result<Self, MtdError> {
+        let mut id = [0u8; 3];
+        device.write_then_read(&[SPINOR_OP_RDID], &mut id)?;
+        // The ID of all zeros or all ones means that there is no flash.
+        if id.iter().all(|byte| *byte == 0) || id.iter().all(|byte| *byte == 0xff) {
+            return Err(MtdError::IoError);
+        }
+        if !(0x10..=0x1f).contains(&id[2]) {
+            return Err(MtdError::NotSupported);
+        }
+
+        let info = FLASH_INFOS.iter().find(|info| info.id == id);
+        let name = info.map_or_else(
+            || format!("spi-nor-{:02x}{:02x}{:02x}", id[0], id[1], id[2]),
+            |info| String::from(info.name),
+        );
+        let size = 1u64 << id[2];
+        let has_4k_sectors = info.is_some_and(|info| info.has_4k_sectors);
+        let has_4b_opcodes = info.is_some_and(|info| info.has_4b_opcodes);
+        let quad_enable = info.map_or(QuadEnable::None, |info| info.quad_enable);
+        let addr_width = if size > 16 * 1024 * 1024 { 4 } else { 3 };
+        // Without the opcodes with 4-byte addresses, the flash is switched to the 4-byte mode.
+        let use_4b_opcodes = addr_width == 4 && has_4b_opcodes;
+
+        // The quad reads of the unknown flashes are not used, since the quad mode may be
+        // disabled in their status registers.
+        let read_width = match device.rx_bus_width() {
+            4 if info.is_some() => 4,
+            width => width.min(2),
+        };
+        let read_opcode = match (read_width, use_4b_opcodes) {
+            (4, false) => SPINOR_OP_READ_1_1_4,
+            (4, true) => SPINOR_OP_READ_1_1_4_4B,
+            (2, false) => SPINOR_OP_READ_1_1_2,
+            (2, true) => SPINOR_OP_READ_1_1_2_4B,
+            (_, false) => SPINOR_OP_READ,
+            (_, true) => SPINOR_OP_READ_4B,
+        };
+        let (erase_size, erase_opcode) = match (has_4k_sectors, use_4b_opcodes) {
+            (true, false) => (4096, SPINOR_OP_BE_4K),
+            (true, true) => (4096, SPINOR_OP_BE_4K_4B),
+            (false, false) => (SECTOR_SIZE, SPINOR_OP_SE),
+            (false, true) => (SECTOR_SIZE, SPINOR_OP_SE_4B),
+        };
+
+        let flash = Self {
+            device,
+            name,
+            size,
+            erase_size,
+            erase_opcode,
+            addr_width,
+            read_opcode,
+            read_width,
+            program_opcode: if use_4b_opcodes {
+                SPINOR_OP_PP_4B
+            } else {
+                SPINOR_OP_PP
+            },
+            lock: Mutex::new(()),
+        };
+        if addr_width == 4 && !use_4b_opcodes {
+            flash.device.write(&[SPINOR_OP_EN4B])?;
+        }
+        if read_width == 4 {
+            flash.enable_quad(quad_enable)?;
+        }
+        Ok(flash)
+    }
+
+    /// Returns the device of the flash.
+    pub fn device(&self) -> &Arc<SpiDevice> {
+        &self.device
+    }
+
+    /// Returns the command with the opcode and the address, whose unused bytes are zeros.
+    fn command(&self, opcode: u8, addr: u64) -> ([u8; 6], usize) {
+        let mut cmd = [0u8; 6];
+        cmd[0] = opcode;
+        let addr_width = self.addr_width as usize;
+        let addr_bytes = (addr as u32).to_be_bytes();
+        cmd[1..=addr_width].copy_from_slice(&addr_bytes[4 - addr_width..]);
+        (cmd, 1 + addr_width)
+    }
+
+    fn read_status(&self, opcode: u8) -> Result<u8, MtdError> {
+        let mut status = [0u8];
+        self.device.write_then_read(&[opcode], &mut status)?;
+        Ok(status[0])
+    }
+
+    /// Waits until the flash completes the program or the erase.
+    fn wait_ready(&self, timeout_us: u64) -> Result<(), MtdError> {
+        let freq = ostd::arch::tsc_freq().max(1);
+        let deadline = ostd::arch::read_tsc() + timeout_us * freq / 1_000_000;
+        loop {
+            if self.read_status(SPINOR_OP_RDSR)? & SR_WIP == 0 {
+                return Ok(());
+            }
+            if ostd::arch::read_tsc() > deadline {
+                // Check once more in case that we were preempted for a long time.
+                return if self.read_status(SPINOR_OP_RDSR)? & SR_WIP == 0 {
+                    Ok(())
+                } else {
+                    Err(MtdError::Timeout)
+                };
+            }
+            // The erases take milliseconds, so let others run.
+            ostd::task::Task::yield_now();
+        }
+    }
+
+    /// Sets the quad enable bit in the status registers, if it is not set.
+    fn enable_quad(&self, quad_enable: QuadEnable) -> Result<(), MtdError> {
+        let (read_opcode, write_opcode, bit) = match quad_enable {
+            QuadEnable::None => return Ok(()),
+            QuadEnable::Sr1Bit6 => (SPINOR_OP_RDSR, SPINOR_OP_WRSR, 1 << 6),
+            QuadEnable::Sr2Bit1 => (SPINOR_OP_RDSR2, SPINOR_OP_WRSR2, 1 << 1),
+        };
+        let status = self.read_status(read_opcode)?;
+        if status & bit != 0 {
+            return Ok(());
+        }
+
+        self.device.write(&[SPINOR_OP_WREN])?;
+        self.device.write(&[write_opcode, status | bit])?;
+        self.wait_ready(PROGRAM_TIMEOUT_US)?;
+        if self.read_status(read_opcode)? & bit == 0 {
+            return Err(MtdError::IoError);
+        }
+        Ok(())
+    }
+}
+
+impl MtdDriver for SpiNor {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn type_(&self) -> MtdType {
+        MtdType::NorFlash
+    }
+
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn erase_size(&self) -> u32 {
+        self.erase_size
+    }
+
+    fn read(&self, offset: u64, buf: &mut [u8]) -> Result<(), MtdError> {
+        if buf.is_empty() {
+            return Ok(());
+        }
+
+        let (cmd, cmd_len) = self.command(self.read_opcode, offset);
+        // The fast reads are followed by 8 dummy cycles, i.e., a dummy byte on a single line.
+        let cmd_len = if self.read_opcode == SPINOR_OP_READ || self.read_opcode == SPINOR_OP_READ_4B
+        {
+            cmd_len
+        } else {
+            cmd_len + 1
+        };
+        let _guard = self.lock.lock();
+        self.device.transfer(&mut [
+            SpiTransfer::write(&cmd[..cmd_len]),
+            SpiTransfer::read(buf).with_bus_width(self.read_width),
+        ])?;
+        Ok(())
+    }
+
+    fn erase(&self, offset: u64, len: u64) -> Result<(), MtdError> {
+        let erase_size = self.erase_size as u64;
+        let _guard = self.lock.lock();
+        for block in (offset..offset + len).step_by(erase_size as usize) {
+            let (cmd, cmd_len) = self.command(self.erase_opcode, block);
+            self.device.write(&[SPINOR_OP_WREN])?;
+            self.device.write(&cmd[..cmd_len])?;
+            self.wait_ready(ERASE_TIMEOUT_US)?;
+        }
+        Ok(())
+    }
+
+    fn write(&self, offset: u64, buf: &[u8]) -> Result<(), MtdError> {
+        let _guard = self.lock.lock();
+        let mut pos = 0;
+        while pos < buf.len() {
+            // A program cannot cross the boundary of the pages.
+            let addr = offset + pos as u64;
+            let page_remain = PAGE_SIZE - (addr as usize % PAGE_SIZE);
+            let len = page_remain.min(buf.len() - pos);
+
+            let (cmd, cmd_len) = self.command(self.program_opcode, addr);
+            self.device.write(&[SPINOR_OP_WREN])?;
+            self.device.transfer(&mut [
+                SpiTransfer::write(&cmd[..cmd_len]),
+                SpiTransfer::write(&buf[pos..pos + len]),
+            ])?;
+            self.wait_ready(PROGRAM_TIMEOUT_US)?;
+            pos += len;
+        }
+        Ok(())
+    }
+}
+
+/// Probes the flashes on the SPI buses, and registers them with their partitions.
+#[cfg(target_arch = "riscv64")]
+pub(crate) fn init() {
+    use log::{info, warn};
+
+    for device in crate::find_devices(SPI_NOR_COMPATIBLES) {
+        let flash = match SpiNor::probe(device.clone()) {
+            Ok(flash) => Arc::new(flash),
+            Err(err) => {
+                warn!(
+                    "[SPI]: spi{}.{}: cannot probe the flash: {:?}",
+                    device.controller().index(),
+                    device.chip_select(),
+                    err
+                );
+                continue;
+            }
+        };
+        info!(
+            "[SPI]: found {} ({} KiB) at spi{}.{}",
+            flash.name(),
+            flash.size() / 1024,
+            device.controller().index(),
+            device.chip_select()
+        );
+        let partitions = aster_mtd::parse_fdt_partitions(device.fdt_node());
+        aster_mtd::register_device(flash, None, partitions);
+    }
+}
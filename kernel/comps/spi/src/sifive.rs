@@ -0,0 +1,268 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The driver of the SiFive SPI/QSPI controller, which is used by the SiFive SoCs.
+//!
+//! The bytes are pushed to and popped from the FIFOs by the registers. The memory-mapped
+//! flash interface of the QSPI controllers is disabled, so that the flashes are accessed
+//! like other devices. The frequency of the clock is only changed if the frequency of its
+//! input is described by a fixed clock in the device tree, and is left as set by the
+//! firmware otherwise.
+//!
+//! Reference: the SPI chapter of the SiFive FU740-C000 manual.
+
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+};
+
+use log::{info, warn};
+use ostd::{arch::boot::DEVICE_TREE, io::MmioRegion, sync::SpinLock};
+
+use crate::{poll_until, SpiControllerDriver, SpiDevice, SpiError, SpiMode, SpiTransfer};
+
+const SIFIVE_SPI_COMPATIBLES: &[&str] = &["sifive,spi0", "sifive,fu540-c000-spi"];
+
+const SIFIVE_SPI_REG_SCKDIV: usize = 0x00;
+const SIFIVE_SPI_REG_SCKMODE: usize = 0x04;
+const SIFIVE_SPI_REG_CSID: usize = 0x10;
+const SIFIVE_SPI_REG_CSDEF: usize = 0x14;
+const SIFIVE_SPI_REG_CSMODE: usize = 0x18;
+const SIFIVE_SPI_REG_FMT: usize = 0x40;
+const SIFIVE_SPI_REG_TXDATA: usize = 0x48;
+const SIFIVE_SPI_REG_RXDATA: usize = 0x4c;
+const SIFIVE_SPI_REG_TXMARK: usize = 0x50;
+const SIFIVE_SPI_REG_FCTRL: usize = 0x60;
+const SIFIVE_SPI_REG_IE: usize = 0x70;
+const SIFIVE_SPI_REG_IP: usize = 0x74;
+
+const SIFIVE_SPI_SCKMODE_PHA: u32 = 1 << 0;
+const SIFIVE_SPI_SCKMODE_POL: u32 = 1 << 1;
+
+const SIFIVE_SPI_CSMODE_AUTO: u32 = 0;
+const SIFIVE_SPI_CSMODE_HOLD: u32 = 2;
+
+const SIFIVE_SPI_FMT_PROTO_SINGLE: u32 = 0;
+const SIFIVE_SPI_FMT_PROTO_DUAL: u32 = 1;
+const SIFIVE_SPI_FMT_PROTO_QUAD: u32 = 2;
+const SIFIVE_SPI_FMT_ENDIAN_LSB: u32 = 1 << 2;
+/// The received bytes are not pushed to the RX FIFO.
+const SIFIVE_SPI_FMT_DIR_TX: u32 = 1 << 3;
+const SIFIVE_SPI_FMT_LEN_SHIFT: u32 = 16;
+
+const SIFIVE_SPI_TXDATA_FULL: u32 = 1 << 31;
+const SIFIVE_SPI_RXDATA_EMPTY: u32 = 1 << 31;
+
+const SIFIVE_SPI_IP_TXWM: u32 = 1 << 0;
+
+/// The default depth of the FIFOs.
+const DEFAULT_FIFO_DEPTH: usize = 8;
+/// The time (in microseconds) to wait for the FIFOs.
+const FIFO_TIMEOUT_US: u64 = 100_000;
+
+struct SifiveSpi {
+    name: String,
+    io_mem: MmioRegion,
+    /// The frequency (in Hz) of the input clock, if known
+    input_hz: Option<u32>,
+    num_cs: u32,
+    fifo_depth: usize,
+    /// The bits of the inactive states of the chip selects
+    cs_inactive: SpinLock<u32>,
+}
+
+/// Probes the controllers in the device tree, and registers them.
+pub(crate) fn init() {
+    let device_tree = DEVICE_TREE.get().unwrap();
+    for node in device_tree.all_nodes() {
+        if !node.compatible().is_some_and(|compatible| {
+            compatible
+                .all()
+                .any(|c| SIFIVE_SPI_COMPATIBLES.contains(&c))
+        }) {
+            continue;
+        }
+        if node
+            .property("status")
+            .and_then(|prop| prop.as_str())
+            .is_some_and(|status| status != "okay" && status != "ok")
+        {
+            continue;
+        }
+
+        let io_mem = match MmioRegion::claim_fdt_reg(&node) {
+            Ok(io_mem) => io_mem,
+            Err(err) => {
+                warn!(
+                    "[SPI]: {}: cannot claim the registers: {:?}",
+                    node.name, err
+                );
+                continue;
+            }
+        };
+        let read_usize = |name: &str| node.property(name).and_then(|prop| prop.as_usize());
+        let input_hz = node
+            .property("clocks")
+            .and_then(|prop| prop.value.get(0..4))
+            .and_then(|phandle| {
+                device_tree.find_phandle(u32::from_be_bytes(phandle.try_into().unwrap()))
+            })
+            .and_then(|clock| clock.property("clock-frequency"))
+            .and_then(|prop| prop.as_usize())
+            .map(|hz| hz as u32);
+
+        let driver = SifiveSpi {
+            name: node.name.to_string(),
+            io_mem,
+            input_hz,
+            num_cs: read_usize("num-cs").unwrap_or(1) as u32,
+            fifo_depth: read_usize("sifive,fifo-depth").unwrap_or(DEFAULT_FIFO_DEPTH),
+            cs_inactive: SpinLock::new(0),
+        };
+        if driver.fifo_depth == 0 || !(1..=32).contains(&driver.num_cs) {
+            warn!("[SPI]: {}: invalid FIFO depth or chip selects", node.name);
+            continue;
+        }
+        driver.reset();
+
+        info!("[SPI]: found SiFive SPI controller {}", node.name);
+        crate::register_fdt_controller(Arc::new(driver), &node);
+    }
+}
+
+impl SifiveSpi {
+    fn reset(&self) {
+        // Disable the interrupts and the memory-mapped flash interface.
+        self.write(SIFIVE_SPI_REG_IE, 0);
+        self.write(SIFIVE_SPI_REG_FCTRL, 0);
+        self.write(SIFIVE_SPI_REG_TXMARK, 1);
+        self.write(SIFIVE_SPI_REG_CSMODE, SIFIVE_SPI_CSMODE_AUTO);
+        // The chip selects are active low by default.
+        let cs_inactive = ((1u64 << self.num_cs) - 1) as u32;
+        *self.cs_inactive.lock() = cs_inactive;
+        self.write(SIFIVE_SPI_REG_CSDEF, cs_inactive);
+        // Drain the RX FIFO.
+        while self.read(SIFIVE_SPI_REG_RXDATA) & SIFIVE_SPI_RXDATA_EMPTY == 0 {}
+    }
+
+    /// Configures the clock and the chip select for the device.
+    fn prepare(&self, device: &SpiDevice) {
+        let mode = device.mode();
+        let mut sckmode = 0;
+        if mode.contains(SpiMode::CPHA) {
+            sckmode |= SIFIVE_SPI_SCKMODE_PHA;
+        }
+        if mode.contains(SpiMode::CPOL) {
+            sckmode |= SIFIVE_SPI_SCKMODE_POL;
+        }
+        self.write(SIFIVE_SPI_REG_SCKMODE, sckmode);
+
+        // The frequency of the clock is `input_hz / (2 * (sckdiv + 1))`.
+        if let Some(input_hz) = self.input_hz {
+            let speed_hz = device.max_speed_hz().max(1);
+            let sckdiv = input_hz.div_ceil(2 * speed_hz).saturating_sub(1);
+            self.write(SIFIVE_SPI_REG_SCKDIV, sckdiv.min(0xfff));
+        }
+
+        let mut cs_inactive = self.cs_inactive.lock();
+        let cs_bit = 1 << device.chip_select();
+        if mode.contains(SpiMode::CS_HIGH) {
+            *cs_inactive &= !cs_bit;
+        } else {
+            *cs_inactive |= cs_bit;
+        }
+        self.write(SIFIVE_SPI_REG_CSDEF, *cs_inactive);
+        self.write(SIFIVE_SPI_REG_CSID, device.chip_select());
+    }
+
+    fn transfer_one(&self, mode: SpiMode, transfer: &mut SpiTransfer) -> Result<(), SpiError> {
+        let proto = match transfer.bus_width {
+            1 => SIFIVE_SPI_FMT_PROTO_SINGLE,
+            2 => SIFIVE_SPI_FMT_PROTO_DUAL,
+            _ => SIFIVE_SPI_FMT_PROTO_QUAD,
+        };
+        let mut fmt = proto | (8 << SIFIVE_SPI_FMT_LEN_SHIFT);
+        if mode.contains(SpiMode::LSB_FIRST) {
+            fmt |= SIFIVE_SPI_FMT_ENDIAN_LSB;
+        }
+        let is_rx = transfer.rx_buf.is_some();
+        if !is_rx {
+            fmt |= SIFIVE_SPI_FMT_DIR_TX;
+        }
+        self.write(SIFIVE_SPI_REG_FMT, fmt);
+
+        let len = transfer.len();
+        let mut pos = 0;
+        while pos < len {
+            let n = (len - pos).min(self.fifo_depth);
+            for index in pos..pos + n {
+                let byte = transfer.tx_buf.map_or(0, |buf| buf[index]);
+                if !poll_until(FIFO_TIMEOUT_US, || {
+                    self.read(SIFIVE_SPI_REG_TXDATA) & SIFIVE_SPI_TXDATA_FULL == 0
+                }) {
+                    return Err(SpiError::Timeout);
+                }
+                self.write(SIFIVE_SPI_REG_TXDATA, byte as u32);
+            }
+
+            if let Some(rx_buf) = transfer.rx_buf.as_mut() {
+                for byte in rx_buf[pos..pos + n].iter_mut() {
+                    let mut data = 0;
+                    if !poll_until(FIFO_TIMEOUT_US, || {
+                        data = self.read(SIFIVE_SPI_REG_RXDATA);
+                        data & SIFIVE_SPI_RXDATA_EMPTY == 0
+                    }) {
+                        return Err(SpiError::Timeout);
+                    }
+                    *byte = data as u8;
+                }
+            } else if !poll_until(FIFO_TIMEOUT_US, || {
+                // The watermark is pending if the TX FIFO is empty.
+                self.read(SIFIVE_SPI_REG_IP) & SIFIVE_SPI_IP_TXWM != 0
+            }) {
+                return Err(SpiError::Timeout);
+            }
+            pos += n;
+        }
+        Ok(())
+    }
+
+    fn read(&self, offset: usize) -> u32 {
+        self.io_mem.reg::<u32>(offset).unwrap().read()
+    }
+
+    fn write(&self, offset: usize, value: u32) {
+        self.io_mem.reg::<u32>(offset).unwrap().write(value);
+    }
+}
+
+impl SpiControllerDriver for SifiveSpi {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn num_chipselect(&self) -> u32 {
+        self.num_cs
+    }
+
+    fn max_bus_width(&self) -> u8 {
+        4
+    }
+
+    fn transfer(&self, device: &SpiDevice, transfers: &mut [SpiTransfer]) -> Result<(), SpiError> {
+        self.prepare(device);
+
+        // The chip select is held between the transfers, and deasserted when the mode is
+        // switched back to the automatic mode.
+        self.write(SIFIVE_SPI_REG_CSMODE, SIFIVE_SPI_CSMODE_HOLD);
+        let result = transfers
+            .iter_mut()
+            .try_for_each(|transfer| self.transfer_one(device.mode(), transfer));
+        self.write(SIFIVE_SPI_REG_CSMODE, SIFIVE_SPI_CSMODE_AUTO);
+
+        if result.is_err() {
+            // Discard the bytes left in the FIFOs.
+            self.reset();
+        }
+        result
+    }
+}
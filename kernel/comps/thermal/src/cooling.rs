@@ -0,0 +1,145 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The cooling devices, which reduce the heat of the system when the zones are hot.
+
+use alloc::{collections::BTreeMap, format, string::String, sync::Arc, vec::Vec};
+
+use aster_cpufreq::CpufreqPolicy;
+use ostd::sync::{LocalIrqDisabled, SpinLock};
+
+/// A cooling device driver.
+///
+/// The cooling states are from zero (no cooling) to the maximum state (the most cooling).
+/// The methods may be called in the interrupt context, so they must not sleep.
+pub trait CoolingDriver: Send + Sync {
+    /// Returns the type of the device, which is the same as Linux (e.g., `cpufreq-cpu0`).
+    fn kind(&self) -> &str;
+
+    /// Returns the maximum cooling state.
+    fn max_state(&self) -> u32;
+
+    /// Sets the cooling state, which is not above the maximum state.
+    fn set_state(&self, state: u32);
+}
+
+/// A registered cooling device.
+///
+/// A device can be throttled by multiple trip points, and its state is the highest state
+/// requested by them.
+pub struct CoolingDevice {
+    index: usize,
+    driver: Arc<dyn CoolingDriver>,
+    phandles: Vec<u32>,
+    state: SpinLock<CoolingState, LocalIrqDisabled>,
+}
+
+struct CoolingState {
+    cur_state: u32,
+    /// The states requested by the cooling maps, indexed by the zones and the maps
+    requests: BTreeMap<(usize, usize), u32>,
+}
+
+impl CoolingDevice {
+    pub(crate) fn new(index: usize, driver: Arc<dyn CoolingDriver>, phandles: Vec<u32>) -> Self {
+        Self {
+            index,
+            driver,
+            phandles,
+            state: SpinLock::new(CoolingState {
+                cur_state: 0,
+                requests: BTreeMap::new(),
+            }),
+        }
+    }
+
+    /// Returns the index of the device.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the type of the device.
+    pub fn kind(&self) -> &str {
+        self.driver.kind()
+    }
+
+    /// Returns the maximum cooling state.
+    pub fn max_state(&self) -> u32 {
+        self.driver.max_state()
+    }
+
+    /// Returns the current cooling state.
+    pub fn cur_state(&self) -> u32 {
+        self.state.lock().cur_state
+    }
+
+    pub(crate) fn phandles(&self) -> &[u32] {
+        &self.phandles
+    }
+
+    /// Requests the cooling state on behalf of a cooling map of a zone.
+    ///
+    /// A request of the zero state is removed.
+    pub(crate) fn request(&self, zone: usize, map: usize, state: u32) {
+        let mut cooling_state = self.state.lock();
+        if state == 0 {
+            cooling_state.requests.remove(&(zone, map));
+        } else {
+            cooling_state.requests.insert((zone, map), state);
+        }
+
+        let new_state = cooling_state
+            .requests
+            .values()
+            .copied()
+            .max()
+            .unwrap_or(0)
+            .min(self.driver.max_state());
+        if new_state != cooling_state.cur_state {
+            cooling_state.cur_state = new_state;
+            self.driver.set_state(new_state);
+        }
+    }
+}
+
+/// A cooling device that caps the frequency of the CPUs in a CPU frequency policy.
+///
+/// Like Linux, the state `N` caps the frequency at the `N`-th highest OPP.
+struct CpufreqCooling {
+    kind: String,
+    policy: Arc<CpufreqPolicy>,
+}
+
+impl CoolingDriver for CpufreqCooling {
+    fn kind(&self) -> &str {
+        &self.kind
+    }
+
+    fn max_state(&self) -> u32 {
+        self.policy.opps().len().saturating_sub(1) as u32
+    }
+
+    fn set_state(&self, state: u32) {
+        let opps = self.policy.opps();
+        let limit = (state > 0).then(|| opps[opps.len() - 1 - state as usize].freq_khz);
+        self.policy.set_thermal_limit(limit);
+    }
+}
+
+/// Registers a cooling device for each CPU frequency policy.
+pub(crate) fn register_cpufreq_cooling_devices() {
+    for policy in aster_cpufreq::all_policies() {
+        let Some(first_cpu) = policy.cpus().iter().next() else {
+            continue;
+        };
+        #[cfg(target_arch = "riscv64")]
+        let phandles = crate::dt::cpu_phandles(policy.cpus());
+        #[cfg(not(target_arch = "riscv64"))]
+        let phandles = Vec::new();
+
+        let driver = CpufreqCooling {
+            kind: format!("cpufreq-cpu{}", first_cpu.as_usize()),
+            policy,
+        };
+        crate::register_cooling_device(Arc::new(driver), phandles);
+    }
+}
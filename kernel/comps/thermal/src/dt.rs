@@ -0,0 +1,172 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The thermal zones in the device tree.
+//!
+//! Each child of the `/thermal-zones` node is a zone, which has:
+//!
+//! - `thermal-sensors`: the phandle of the sensor, followed by the ID of the sensor if the
+//!   sensor node has a non-zero `#thermal-sensor-cells`;
+//! - `polling-delay` and `polling-delay-passive`: the polling intervals in milliseconds;
+//! - `trips`: the trip points with `temperature`, `hysteresis` (both in millidegrees Celsius)
+//!   and `type`;
+//! - `cooling-maps`: the maps from the trip points (by `trip`) to the cooling devices (by
+//!   `cooling-device`, which is the phandle of the device followed by the minimum and the
+//!   maximum cooling states).
+//!
+//! Reference: <https://www.kernel.org/doc/Documentation/devicetree/bindings/thermal/thermal-zones.yaml>
+
+use alloc::{string::ToString, vec::Vec};
+
+use fdt::node::FdtNode;
+use log::warn;
+use ostd::{
+    arch::{boot::DEVICE_TREE, hart},
+    cpu::CpuSet,
+};
+
+use crate::{find_cooling_device, ThermalZone, Trip, TripType};
+
+/// The cooling state in the cooling maps that means no limit.
+const THERMAL_NO_LIMIT: u32 = u32::MAX;
+
+/// Parses the thermal zones in the device tree.
+pub(crate) fn parse_thermal_zones() -> Vec<ThermalZone> {
+    let Some(zones_node) = DEVICE_TREE.get().unwrap().find_node("/thermal-zones") else {
+        return Vec::new();
+    };
+
+    zones_node
+        .children()
+        .filter(is_enabled)
+        .filter_map(|node| {
+            let zone = parse_zone(&node);
+            if zone.is_none() {
+                warn!("[Thermal]: {}: invalid thermal zone", node.name);
+            }
+            zone
+        })
+        .collect()
+}
+
+/// Returns the phandles of the CPU nodes of the CPUs.
+pub(crate) fn cpu_phandles(cpus: &CpuSet) -> Vec<u32> {
+    DEVICE_TREE
+        .get()
+        .unwrap()
+        .all_nodes()
+        .filter(|node| node.name.starts_with("cpu@"))
+        .filter(|node| {
+            node.reg()
+                .and_then(|mut reg| reg.next())
+                .map(|reg| reg.starting_address as usize)
+                .and_then(hart::cpu_id)
+                .is_some_and(|cpu| cpus.contains(cpu))
+        })
+        .filter_map(|node| read_u32(&node, "phandle"))
+        .collect()
+}
+
+fn parse_zone(node: &FdtNode) -> Option<ThermalZone> {
+    let sensors = node.property("thermal-sensors")?.value;
+    let sensor_phandle = read_be_u32(sensors, 0)?;
+    let sensor_id = read_be_u32(sensors, 1).unwrap_or(0);
+    let polling_delay_ms = read_u32(node, "polling-delay").unwrap_or(0);
+    let passive_delay_ms = read_u32(node, "polling-delay-passive").unwrap_or(0);
+
+    // The phandles of the trip points, which are referred to by the cooling maps.
+    let mut trip_phandles = Vec::new();
+    let mut trips = Vec::new();
+    for trip_node in node
+        .children()
+        .find(|child| child.name == "trips")
+        .into_iter()
+        .flat_map(|trips_node| trips_node.children())
+    {
+        let Some(kind) = trip_node
+            .property("type")
+            .and_then(|prop| prop.as_str())
+            .and_then(TripType::from_name)
+        else {
+            warn!("[Thermal]: {}: unknown trip type", trip_node.name);
+            continue;
+        };
+        let Some(temperature) = read_u32(&trip_node, "temperature") else {
+            continue;
+        };
+        trips.push(Trip {
+            // The temperatures are signed in the device tree.
+            temperature: temperature as i32,
+            hysteresis: read_u32(&trip_node, "hysteresis").unwrap_or(0) as i32,
+            kind,
+        });
+        trip_phandles.push(read_u32(&trip_node, "phandle"));
+    }
+
+    let mut zone = ThermalZone::new(
+        node.name.to_string(),
+        sensor_phandle,
+        sensor_id,
+        polling_delay_ms,
+        passive_delay_ms,
+        trips,
+    );
+
+    for map_node in node
+        .children()
+        .find(|child| child.name == "cooling-maps")
+        .into_iter()
+        .flat_map(|maps_node| maps_node.children())
+    {
+        let Some(trip) = read_u32(&map_node, "trip")
+            .and_then(|phandle| trip_phandles.iter().position(|p| *p == Some(phandle)))
+        else {
+            continue;
+        };
+        let Some(devices) = map_node.property("cooling-device") else {
+            continue;
+        };
+
+        for index in 0..devices.value.len() / 12 {
+            let Some(phandle) = read_be_u32(devices.value, index * 3) else {
+                continue;
+            };
+            let Some(device) = find_cooling_device(phandle) else {
+                warn!(
+                    "[Thermal]: {}: unknown cooling device {}",
+                    map_node.name, phandle
+                );
+                continue;
+            };
+            let min_state = read_be_u32(devices.value, index * 3 + 1)
+                .filter(|state| *state != THERMAL_NO_LIMIT)
+                .unwrap_or(0);
+            let max_state = read_be_u32(devices.value, index * 3 + 2)
+                .filter(|state| *state != THERMAL_NO_LIMIT)
+                .unwrap_or(device.max_state());
+            if zone
+                .add_cooling_map(trip, device, min_state, max_state)
+                .is_err()
+            {
+                warn!("[Thermal]: {}: invalid cooling states", map_node.name);
+            }
+        }
+    }
+
+    Some(zone)
+}
+
+fn is_enabled(node: &FdtNode) -> bool {
+    node.property("status")
+        .and_then(|prop| prop.as_str())
+        .is_none_or(|status| status == "okay" || status == "ok")
+}
+
+fn read_u32(node: &FdtNode, name: &str) -> Option<u32> {
+    read_be_u32(node.property(name)?.value, 0)
+}
+
+/// Reads the big-endian `u32` at the index of the cells.
+fn read_be_u32(value: &[u8], index: usize) -> Option<u32> {
+    let bytes = value.get(index * 4..index * 4 + 4)?;
+    Some(u32::from_be_bytes(bytes.try_into().unwrap()))
+}
@@ -0,0 +1,209 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The thermal subsystem of Asterinas.
+//!
+//! A thermal zone is a part of the system whose temperature is measured by a
+//! [`ThermalSensor`]. Its trip points describe what to do when the zone becomes hot:
+//!
+//! - the passive and the active trip points throttle the [`CoolingDevice`]s that are mapped to
+//!   them, e.g., by capping the frequency of the CPUs;
+//! - the hot trip points report the event;
+//! - the critical trip points shut down the system.
+//!
+//! The zones, their trip points and their cooling maps are described by the `thermal-zones`
+//! node of the device tree. The temperatures are polled with the system timer, and the sensors
+//! that raise interrupts can report the changes by [`notify_sensor_event`].
+//!
+//! The following drivers are provided:
+//!
+//! - `sfctemp`: the temperature sensor of the StarFive JH7110 SoC, which is discovered through
+//!   the device tree.
+//!
+//! A cooling device is registered for each CPU frequency policy.
+
+#![no_std]
+#![deny(unsafe_code)]
+
+extern crate alloc;
+
+mod cooling;
+#[cfg(target_arch = "riscv64")]
+mod dt;
+#[cfg(target_arch = "riscv64")]
+mod sfctemp;
+mod zone;
+
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use component::{init_component, ComponentInitError};
+use log::info;
+use ostd::{
+    sync::{LocalIrqDisabled, SpinLock},
+    timer::{self, Jiffies},
+};
+
+pub use self::{
+    cooling::{CoolingDevice, CoolingDriver},
+    zone::{ThermalZone, ThermalZoneMode, Trip, TripType, GOVERNOR_NAME},
+};
+
+/// The interval (in milliseconds) between two checks of the polling deadlines.
+const CHECK_INTERVAL_MS: u64 = 10;
+
+static SENSORS: SpinLock<BTreeMap<u32, Arc<dyn ThermalSensor>>, LocalIrqDisabled> =
+    SpinLock::new(BTreeMap::new());
+static ZONES: SpinLock<Vec<Arc<ThermalZone>>, LocalIrqDisabled> = SpinLock::new(Vec::new());
+static COOLING_DEVICES: SpinLock<Vec<Arc<CoolingDevice>>, LocalIrqDisabled> =
+    SpinLock::new(Vec::new());
+
+#[init_component]
+fn thermal_component_init() -> Result<(), ComponentInitError> {
+    cooling::register_cpufreq_cooling_devices();
+    #[cfg(target_arch = "riscv64")]
+    {
+        sfctemp::init();
+        for zone in dt::parse_thermal_zones() {
+            register_zone(zone);
+        }
+    }
+
+    timer::register_callback(poll_zones);
+    Ok(())
+}
+
+/// The errors of thermal sensors.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ThermalError {
+    /// The sensor is not found, or the sensor ID is invalid.
+    InvalidArgs,
+    /// The temperature is not available yet.
+    Busy,
+    /// The hardware reports an error.
+    IoError,
+}
+
+/// A temperature sensor.
+///
+/// The methods may be called in the interrupt context, so they must not sleep.
+pub trait ThermalSensor: Send + Sync {
+    /// Returns the name of the sensor.
+    fn name(&self) -> &str;
+
+    /// Returns the temperature (in millidegrees Celsius) measured by the sensor with the ID.
+    ///
+    /// The ID is the specifier of `thermal-sensors` in the device tree, which is zero for the
+    /// devices with only one sensor.
+    fn read_temp(&self, id: u32) -> Result<i32, ThermalError>;
+}
+
+/// Registers a temperature sensor with the phandle of its node in the device tree.
+///
+/// The zones that refer to the sensor can be updated since then.
+pub fn register_sensor(phandle: u32, sensor: Arc<dyn ThermalSensor>) {
+    info!("[Thermal]: registered sensor {}", sensor.name());
+    SENSORS.lock().insert(phandle, sensor);
+}
+
+/// Updates the zones that are measured by the sensor with the phandle.
+///
+/// This function should be called by the drivers when their sensors raise the interrupts
+/// (e.g., when the temperatures cross the thresholds).
+pub fn notify_sensor_event(phandle: u32) {
+    for zone in all_zones() {
+        if zone.sensor_phandle() == phandle {
+            zone.update(now_ms());
+        }
+    }
+}
+
+fn find_sensor(phandle: u32) -> Option<Arc<dyn ThermalSensor>> {
+    SENSORS.lock().get(&phandle).cloned()
+}
+
+/// Registers a thermal zone.
+pub fn register_zone(zone: ThermalZone) -> Arc<ThermalZone> {
+    let mut zones = ZONES.lock();
+    let zone = Arc::new(zone.with_index(zones.len()));
+    info!(
+        "[Thermal]: registered thermal_zone{} ({}) with {} trip points",
+        zone.index(),
+        zone.name(),
+        zone.trips().len()
+    );
+    zones.push(zone.clone());
+    zone
+}
+
+/// Returns the thermal zone with the index.
+pub fn get_zone(index: usize) -> Option<Arc<ThermalZone>> {
+    ZONES.lock().get(index).cloned()
+}
+
+/// Returns all the thermal zones.
+pub fn all_zones() -> Vec<Arc<ThermalZone>> {
+    ZONES.lock().clone()
+}
+
+/// Registers a cooling device, returning the registered cooling device.
+///
+/// The phandles are those of the nodes in the device tree that refer to the device in the
+/// cooling maps (e.g., the CPU nodes for the CPU frequency).
+pub fn register_cooling_device(
+    driver: Arc<dyn CoolingDriver>,
+    phandles: Vec<u32>,
+) -> Arc<CoolingDevice> {
+    let mut devices = COOLING_DEVICES.lock();
+    let device = Arc::new(CoolingDevice::new(devices.len(), driver, phandles));
+    info!(
+        "[Thermal]: registered cooling_device{} ({}) with {} states",
+        device.index(),
+        device.kind(),
+        device.max_state() + 1
+    );
+    devices.push(device.clone());
+    device
+}
+
+/// Returns the cooling device with the index.
+pub fn get_cooling_device(index: usize) -> Option<Arc<CoolingDevice>> {
+    COOLING_DEVICES.lock().get(index).cloned()
+}
+
+/// Returns all the cooling devices.
+pub fn all_cooling_devices() -> Vec<Arc<CoolingDevice>> {
+    COOLING_DEVICES.lock().clone()
+}
+
+fn find_cooling_device(phandle: u32) -> Option<Arc<CoolingDevice>> {
+    COOLING_DEVICES
+        .lock()
+        .iter()
+        .find(|device| device.phandles().contains(&phandle))
+        .cloned()
+}
+
+/// Returns the time since boot in milliseconds.
+fn now_ms() -> u64 {
+    Jiffies::elapsed().as_duration().as_millis() as u64
+}
+
+fn poll_zones() {
+    static LAST_CHECK: AtomicU64 = AtomicU64::new(0);
+
+    let now = now_ms();
+    let last_check = LAST_CHECK.load(Ordering::Relaxed);
+    if now < last_check + CHECK_INTERVAL_MS
+        || LAST_CHECK
+            .compare_exchange(last_check, now, Ordering::Relaxed, Ordering::Relaxed)
+            .is_err()
+    {
+        return;
+    }
+
+    for zone in all_zones() {
+        if zone.is_polling_due(now) {
+            zone.update(now);
+        }
+    }
+}
@@ -0,0 +1,131 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The driver of the temperature sensor of the StarFive JH7110 SoC.
+//!
+//! The sensor has a single register. After it is powered up and started, the digital output
+//! is updated continuously, which is converted to the temperature linearly.
+//!
+//! The clock controllers and the reset controllers are not supported, so the clocks and the
+//! resets of the sensor are expected to be enabled by the firmware.
+
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+};
+use core::hint::spin_loop;
+
+use log::{info, warn};
+use ostd::{arch::boot::DEVICE_TREE, io::MmioRegion};
+
+use crate::{ThermalError, ThermalSensor};
+
+const SFCTEMP_COMPATIBLES: &[&str] = &["starfive,jh7110-temp"];
+
+const SFCTEMP_RSTN: u32 = 1 << 0;
+const SFCTEMP_PD: u32 = 1 << 1;
+const SFCTEMP_RUN: u32 = 1 << 2;
+const SFCTEMP_DOUT_SHIFT: u32 = 16;
+const SFCTEMP_DOUT_MASK: u32 = 0xfff;
+
+// The conversion from the digital output to millidegrees Celsius.
+const SFCTEMP_Y1000: i64 = 237500;
+const SFCTEMP_Z: i64 = 4094;
+const SFCTEMP_K1000: i64 = 81100;
+
+struct Sfctemp {
+    name: String,
+    io_mem: MmioRegion,
+}
+
+/// Probes the sensors in the device tree, and registers them.
+pub(crate) fn init() {
+    for node in DEVICE_TREE.get().unwrap().all_nodes() {
+        if !node
+            .compatible()
+            .is_some_and(|compatible| compatible.all().any(|c| SFCTEMP_COMPATIBLES.contains(&c)))
+        {
+            continue;
+        }
+        if node
+            .property("status")
+            .and_then(|prop| prop.as_str())
+            .is_some_and(|status| status != "okay" && status != "ok")
+        {
+            continue;
+        }
+        let Some(phandle) = node.property("phandle").and_then(|prop| prop.as_usize()) else {
+            continue;
+        };
+
+        let io_mem = match MmioRegion::claim_fdt_reg(&node) {
+            Ok(io_mem) => io_mem,
+            Err(err) => {
+                warn!(
+                    "[Thermal]: {}: cannot claim the registers: {:?}",
+                    node.name, err
+                );
+                continue;
+            }
+        };
+        let sensor = Sfctemp {
+            name: node.name.to_string(),
+            io_mem,
+        };
+        if sensor.start().is_err() {
+            warn!("[Thermal]: {}: cannot start the sensor", node.name);
+            continue;
+        }
+
+        info!("[Thermal]: found JH7110 temperature sensor {}", node.name);
+        crate::register_sensor(phandle as u32, Arc::new(sensor));
+    }
+}
+
+impl Sfctemp {
+    fn start(&self) -> Result<(), ThermalError> {
+        // Power up the sensor. See the datasheet for the sequence and the delays.
+        self.write(SFCTEMP_PD)?;
+        delay_us(1);
+        self.write(0)?;
+        delay_us(60);
+        self.write(SFCTEMP_RSTN)?;
+        delay_us(60);
+
+        self.write(SFCTEMP_RSTN | SFCTEMP_RUN)?;
+        delay_us(1);
+        Ok(())
+    }
+
+    fn write(&self, value: u32) -> Result<(), ThermalError> {
+        self.io_mem
+            .reg::<u32>(0)
+            .map(|reg| reg.write(value))
+            .map_err(|_| ThermalError::IoError)
+    }
+}
+
+impl ThermalSensor for Sfctemp {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn read_temp(&self, id: u32) -> Result<i32, ThermalError> {
+        if id != 0 {
+            return Err(ThermalError::InvalidArgs);
+        }
+        let value = self
+            .io_mem
+            .reg::<u32>(0)
+            .map(|reg| reg.read())
+            .map_err(|_| ThermalError::IoError)?;
+        let dout = ((value >> SFCTEMP_DOUT_SHIFT) & SFCTEMP_DOUT_MASK) as i64;
+        Ok((dout * SFCTEMP_Y1000 / SFCTEMP_Z - SFCTEMP_K1000) as i32)
+    }
+}
+
+fn delay_us(us: u64) {
+    let deadline = ostd::arch::read_tsc() + us * ostd::arch::tsc_freq() / 1_000_000;
+    while ostd::arch::read_tsc() < deadline {
+        spin_loop();
+    }
+}
@@ -0,0 +1,309 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The thermal zones and their trip points.
+
+use alloc::{string::String, sync::Arc, vec, vec::Vec};
+
+use log::{error, warn};
+use ostd::{
+    arch::qemu::{exit_qemu, QemuExitCode},
+    sync::{LocalIrqDisabled, SpinLock},
+};
+
+use crate::{find_sensor, CoolingDevice, ThermalError};
+
+/// The name of the governor that throttles the cooling devices.
+///
+/// Like the `step_wise` governor of Linux, the state of a cooling device is raised by one each
+/// time the temperature rises above a trip point, and lowered by one each time the
+/// temperature is below the trip point.
+pub const GOVERNOR_NAME: &str = "step_wise";
+
+/// The type of a trip point.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TripType {
+    /// Turns on the active cooling devices (e.g., the fans).
+    Active,
+    /// Throttles the passive cooling devices (e.g., the CPU frequency).
+    Passive,
+    /// Reports that the zone is too hot.
+    Hot,
+    /// Shuts down the system.
+    Critical,
+}
+
+impl TripType {
+    /// Returns the name of the type, which is the same as Linux.
+    pub fn name(&self) -> &'static str {
+        match self {
+            TripType::Active => "active",
+            TripType::Passive => "passive",
+            TripType::Hot => "hot",
+            TripType::Critical => "critical",
+        }
+    }
+
+    /// Returns the type with the name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        [
+            TripType::Active,
+            TripType::Passive,
+            TripType::Hot,
+            TripType::Critical,
+        ]
+        .into_iter()
+        .find(|kind| kind.name() == name)
+    }
+}
+
+/// A trip point of a thermal zone.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Trip {
+    /// The temperature (in millidegrees Celsius) at which the trip point is crossed
+    pub temperature: i32,
+    /// The difference (in millidegrees Celsius) below the temperature at which the trip
+    /// point is no longer crossed
+    pub hysteresis: i32,
+    /// The type of the trip point
+    pub kind: TripType,
+}
+
+/// The mode of a thermal zone.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ThermalZoneMode {
+    /// The zone is monitored.
+    Enabled,
+    /// The zone is not monitored, and its cooling devices are released.
+    Disabled,
+}
+
+/// A thermal zone.
+pub struct ThermalZone {
+    index: usize,
+    name: String,
+    sensor_phandle: u32,
+    sensor_id: u32,
+    /// The interval (in milliseconds) between two polls, or zero if the zone is not polled
+    polling_delay_ms: u32,
+    /// The interval (in milliseconds) between two polls when passive cooling is in progress
+    passive_delay_ms: u32,
+    trips: Vec<Trip>,
+    maps: Vec<CoolingMap>,
+    state: SpinLock<ZoneState, LocalIrqDisabled>,
+}
+
+/// A cooling device that is throttled when a trip point is crossed.
+struct CoolingMap {
+    trip: usize,
+    device: Arc<CoolingDevice>,
+    min_state: u32,
+    max_state: u32,
+}
+
+struct ZoneState {
+    mode: ThermalZoneMode,
+    /// The last temperature (in millidegrees Celsius)
+    temperature: Option<i32>,
+    last_update_ms: u64,
+    is_passive: bool,
+    /// Whether the trip points are crossed
+    tripped: Vec<bool>,
+    /// The states requested by the cooling maps
+    map_states: Vec<u32>,
+}
+
+impl ThermalZone {
+    /// Creates a zone that is measured by the sensor with the phandle and the ID.
+    pub fn new(
+        name: String,
+        sensor_phandle: u32,
+        sensor_id: u32,
+        polling_delay_ms: u32,
+        passive_delay_ms: u32,
+        trips: Vec<Trip>,
+    ) -> Self {
+        let nr_trips = trips.len();
+        Self {
+            index: 0,
+            name,
+            sensor_phandle,
+            sensor_id,
+            polling_delay_ms,
+            passive_delay_ms,
+            trips,
+            maps: Vec::new(),
+            state: SpinLock::new(ZoneState {
+                mode: ThermalZoneMode::Enabled,
+                temperature: None,
+                last_update_ms: 0,
+                is_passive: false,
+                tripped: vec![false; nr_trips],
+                map_states: Vec::new(),
+            }),
+        }
+    }
+
+    /// Throttles the cooling device between the states when the trip point is crossed.
+    pub fn add_cooling_map(
+        &mut self,
+        trip: usize,
+        device: Arc<CoolingDevice>,
+        min_state: u32,
+        max_state: u32,
+    ) -> Result<(), ThermalError> {
+        let max_state = max_state.min(device.max_state());
+        if trip >= self.trips.len() || min_state > max_state {
+            return Err(ThermalError::InvalidArgs);
+        }
+
+        self.maps.push(CoolingMap {
+            trip,
+            device,
+            min_state,
+            max_state,
+        });
+        self.state.get_mut().map_states.push(0);
+        Ok(())
+    }
+
+    pub(crate) fn with_index(mut self, index: usize) -> Self {
+        self.index = index;
+        self
+    }
+
+    /// Returns the index of the zone.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the name of the zone.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub(crate) fn sensor_phandle(&self) -> u32 {
+        self.sensor_phandle
+    }
+
+    /// Returns the trip points.
+    pub fn trips(&self) -> &[Trip] {
+        &self.trips
+    }
+
+    /// Returns the cooling devices that are mapped to the trip points.
+    pub fn cooling_devices(&self) -> impl Iterator<Item = (usize, &Arc<CoolingDevice>)> {
+        self.maps.iter().map(|map| (map.trip, &map.device))
+    }
+
+    /// Reads the current temperature (in millidegrees Celsius) from the sensor.
+    pub fn temperature(&self) -> Result<i32, ThermalError> {
+        find_sensor(self.sensor_phandle)
+            .ok_or(ThermalError::InvalidArgs)?
+            .read_temp(self.sensor_id)
+    }
+
+    /// Returns the mode.
+    pub fn mode(&self) -> ThermalZoneMode {
+        self.state.lock().mode
+    }
+
+    /// Sets the mode.
+    ///
+    /// The cooling devices are released if the zone is disabled.
+    pub fn set_mode(&self, mode: ThermalZoneMode) {
+        let mut state = self.state.lock();
+        state.mode = mode;
+        if mode == ThermalZoneMode::Disabled {
+            state.temperature = None;
+            state.is_passive = false;
+            state.tripped.fill(false);
+            for (index, map) in self.maps.iter().enumerate() {
+                state.map_states[index] = 0;
+                map.device.request(self.index, index, 0);
+            }
+        }
+    }
+
+    pub(crate) fn is_polling_due(&self, now_ms: u64) -> bool {
+        let state = self.state.lock();
+        let delay = if state.is_passive && self.passive_delay_ms > 0 {
+            self.passive_delay_ms
+        } else {
+            self.polling_delay_ms
+        };
+        delay > 0 && now_ms >= state.last_update_ms + delay as u64
+    }
+
+    /// Reads the temperature, and handles the trip points.
+    pub(crate) fn update(&self, now_ms: u64) {
+        let mut state = self.state.lock();
+        state.last_update_ms = now_ms;
+        if state.mode == ThermalZoneMode::Disabled {
+            return;
+        }
+
+        let temperature = match self.temperature() {
+            Ok(temperature) => temperature,
+            Err(err) => {
+                warn!(
+                    "[Thermal]: thermal_zone{}: cannot read the temperature: {:?}",
+                    self.index, err
+                );
+                return;
+            }
+        };
+        let last_temperature = state.temperature.replace(temperature);
+
+        for (index, trip) in self.trips.iter().enumerate() {
+            let was_tripped = state.tripped[index];
+            let is_tripped = if was_tripped {
+                temperature > trip.temperature - trip.hysteresis
+            } else {
+                temperature >= trip.temperature
+            };
+            state.tripped[index] = is_tripped;
+
+            match trip.kind {
+                TripType::Critical if is_tripped => {
+                    error!(
+                        "[Thermal]: thermal_zone{} ({}) reached the critical temperature {} mC, shutting down",
+                        self.index, self.name, temperature
+                    );
+                    exit_qemu(QemuExitCode::Failed);
+                }
+                TripType::Hot if is_tripped && !was_tripped => error!(
+                    "[Thermal]: thermal_zone{} ({}) reached the hot temperature {} mC",
+                    self.index, self.name, temperature
+                ),
+                _ => {}
+            }
+        }
+
+        let is_rising = last_temperature.is_none_or(|last| temperature >= last);
+        let mut is_passive = false;
+        for (index, map) in self.maps.iter().enumerate() {
+            let cur_state = state.map_states[index];
+            let new_state = if !state.tripped[map.trip] {
+                // Release the device step by step.
+                if cur_state <= map.min_state {
+                    0
+                } else {
+                    cur_state - 1
+                }
+            } else if is_rising {
+                (cur_state + 1).max(map.min_state).min(map.max_state)
+            } else {
+                cur_state.max(map.min_state)
+            };
+
+            if self.trips[map.trip].kind == TripType::Passive && new_state > 0 {
+                is_passive = true;
+            }
+            if new_state != cur_state {
+                state.map_states[index] = new_state;
+                map.device.request(self.index, index, new_state);
+            }
+        }
+        state.is_passive = is_passive;
+    }
+}
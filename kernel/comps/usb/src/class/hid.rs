@@ -0,0 +1,290 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The USB HID class driver for keyboards.
+//!
+//! The keyboards are switched to the boot protocol, whose reports have a fixed layout: a
+//! byte of the modifier keys, a reserved byte, and up to 6 pressed keys in HID usages. The
+//! reports are compared with the previous ones to generate the key events.
+//!
+//! The interrupt endpoints are polled in the timer callback, and each keyboard is registered
+//! as an input device named `usb-kbdN` in `aster-input`.
+
+use alloc::{format, sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use aster_input::{
+    event::BUS_USB,
+    key::{Key, KeyStatus},
+    InputDevice, InputEvent, InputId,
+};
+use log::{info, warn};
+use ostd::{
+    mm::{DmaDirection, DmaStream, DmaStreamSlice, FrameAllocOptions, VmIo},
+    sync::{LocalIrqDisabled, RwLock, SpinLock},
+    timer,
+};
+use spin::Once;
+
+use super::UsbDriver;
+use crate::{
+    descriptor::{EndpointType, InterfaceDescriptor, RECIP_INTERFACE, TYPE_CLASS},
+    device::UsbDevice,
+    host::{Urb, UsbError},
+};
+
+// The class, the subclass, and the protocol of the supported interfaces.
+const CLASS_HID: u8 = 0x03;
+const SUBCLASS_BOOT: u8 = 0x01;
+const PROTOCOL_KEYBOARD: u8 = 0x01;
+
+// The class requests.
+const SET_IDLE: u8 = 0x0A;
+const SET_PROTOCOL: u8 = 0x0B;
+/// The boot protocol (as opposed to the report protocol).
+const BOOT_PROTOCOL: u16 = 0;
+
+/// The length of the boot protocol reports.
+const REPORT_LEN: usize = 8;
+/// The usage of the keys that indicates too many keys are pressed.
+const USAGE_ROLLOVER: u8 = 0x01;
+
+/// The Linux key codes of the modifier keys, in the order of the bits of the modifier byte.
+const MODIFIER_KEYS: [u16; 8] = [29, 42, 56, 125, 97, 54, 100, 126];
+
+/// The Linux key codes of the HID usages in the keyboard page, starting from usage 0.
+#[rustfmt::skip]
+const USAGE_KEYS: [u16; 102] = [
+      0,   0,   0,   0,  30,  48,  46,  32,  18,  33,  34,  35,  23,  36,  37,  38,
+     50,  49,  24,  25,  16,  19,  31,  20,  22,  47,  17,  45,  21,  44,   2,   3,
+      4,   5,   6,   7,   8,   9,  10,  11,  28,   1,  14,  15,  57,  12,  13,  26,
+     27,  43,  43,  39,  40,  41,  51,  52,  53,  58,  59,  60,  61,  62,  63,  64,
+     65,  66,  67,  68,  87,  88,  99,  70, 119, 110, 102, 104, 111, 107, 109, 106,
+    105, 108, 103,  69,  98,  55,  74,  78,  96,  79,  80,  81,  75,  76,  77,  71,
+     72,  73,  82,  83,  86, 127,
+];
+
+/// The HID class driver.
+#[derive(Debug)]
+pub(crate) struct HidDriver;
+
+impl UsbDriver for HidDriver {
+    fn name(&self) -> &str {
+        "usbhid"
+    }
+
+    fn probe(&self, device: &Arc<UsbDevice>, interface: &InterfaceDescriptor) -> bool {
+        if interface.class != CLASS_HID
+            || interface.subclass != SUBCLASS_BOOT
+            || interface.protocol != PROTOCOL_KEYBOARD
+        {
+            return false;
+        }
+
+        let keyboard = match UsbKeyboard::new(device.clone(), interface) {
+            Ok(keyboard) => Arc::new(keyboard),
+            Err(err) => {
+                warn!(
+                    "[USB]: {}: keyboard initialization failed: {:?}",
+                    device.name(),
+                    err
+                );
+                return true;
+            }
+        };
+
+        let name = format!("usb-kbd{}", NEXT_INDEX.fetch_add(1, Ordering::Relaxed));
+        info!("[USB]: {}: keyboard, device {}", name, device.name());
+        aster_input::register_device(name, keyboard.clone());
+        KEYBOARDS.lock().push(keyboard);
+        TIMER_CALLBACK.call_once(|| timer::register_callback(poll_keyboards));
+        true
+    }
+}
+
+/// The index of the next `usb-kbdN` device.
+static NEXT_INDEX: AtomicUsize = AtomicUsize::new(0);
+
+static KEYBOARDS: SpinLock<Vec<Arc<UsbKeyboard>>, LocalIrqDisabled> = SpinLock::new(Vec::new());
+
+static TIMER_CALLBACK: Once<()> = Once::new();
+
+/// A USB keyboard.
+#[derive(Debug)]
+pub struct UsbKeyboard {
+    device: Arc<UsbDevice>,
+    endpoint: u8,
+    buffer: DmaStream,
+    state: SpinLock<KeyboardState, LocalIrqDisabled>,
+    #[expect(clippy::type_complexity)]
+    callbacks: RwLock<Vec<Arc<dyn Fn(InputEvent) + Send + Sync + 'static>>, LocalIrqDisabled>,
+}
+
+#[derive(Debug)]
+struct KeyboardState {
+    /// The pending transfer, or `None` if the keyboard stops working.
+    urb: Option<Arc<Urb>>,
+    last_report: [u8; REPORT_LEN],
+}
+
+impl UsbKeyboard {
+    fn new(device: Arc<UsbDevice>, interface: &InterfaceDescriptor) -> Result<Self, UsbError> {
+        let endpoint = interface
+            .find_endpoint(EndpointType::Interrupt, true)
+            .ok_or(UsbError::InvalidDescriptor)?
+            .address;
+        let buffer = {
+            let segment = FrameAllocOptions::new()
+                .alloc_segment(1)
+                .map_err(|_| UsbError::NoResource)?;
+            DmaStream::map(segment.into(), DmaDirection::FromDevice, false)
+                .map_err(|_| UsbError::NoResource)?
+        };
+
+        let index = interface.number as u16;
+        device.control_out(
+            TYPE_CLASS | RECIP_INTERFACE,
+            SET_PROTOCOL,
+            BOOT_PROTOCOL,
+            index,
+            &[],
+        )?;
+        // Only report the changes. Some keyboards do not support this request.
+        let _ = device.control_out(TYPE_CLASS | RECIP_INTERFACE, SET_IDLE, 0, index, &[]);
+
+        let keyboard = Self {
+            device,
+            endpoint,
+            buffer,
+            state: SpinLock::new(KeyboardState {
+                urb: None,
+                last_report: [0; REPORT_LEN],
+            }),
+            callbacks: RwLock::new(Vec::new()),
+        };
+        let urb = keyboard.submit()?;
+        keyboard.state.lock().urb = Some(urb);
+        Ok(keyboard)
+    }
+
+    fn submit(&self) -> Result<Arc<Urb>, UsbError> {
+        let slice = DmaStreamSlice::new(self.buffer.clone(), 0, REPORT_LEN);
+        let urb = Urb::new(self.endpoint, slice);
+        self.device.submit(&urb)?;
+        Ok(urb)
+    }
+
+    /// Handles the completed transfer, and submits the next one.
+    fn poll(&self) {
+        let mut state = self.state.lock();
+        let Some(result) = state.urb.as_ref().and_then(|urb| urb.result()) else {
+            return;
+        };
+
+        match result {
+            Ok(len) if len == REPORT_LEN => {
+                let slice = DmaStreamSlice::new(self.buffer.clone(), 0, REPORT_LEN);
+                slice.sync().unwrap();
+                let mut report = [0u8; REPORT_LEN];
+                slice.read_bytes(0, &mut report).unwrap();
+                self.handle_report(&state.last_report, &report);
+                // Ignore the reports with the rollover errors.
+                if report[2] != USAGE_ROLLOVER {
+                    state.last_report = report;
+                }
+            }
+            Ok(_) => {}
+            Err(err) => {
+                warn!("[USB]: {}: keyboard stopped: {:?}", self.device.name(), err);
+                state.urb = None;
+                return;
+            }
+        }
+
+        match self.submit() {
+            Ok(urb) => state.urb = Some(urb),
+            Err(err) => {
+                warn!("[USB]: {}: keyboard stopped: {:?}", self.device.name(), err);
+                state.urb = None;
+            }
+        }
+    }
+
+    fn handle_report(&self, last: &[u8; REPORT_LEN], report: &[u8; REPORT_LEN]) {
+        if report[2] == USAGE_ROLLOVER {
+            return;
+        }
+
+        let changed_modifiers = last[0] ^ report[0];
+        for (bit, code) in MODIFIER_KEYS.iter().enumerate() {
+            if changed_modifiers & (1 << bit) == 0 {
+                continue;
+            }
+            let status = if report[0] & (1 << bit) != 0 {
+                KeyStatus::Pressed
+            } else {
+                KeyStatus::Released
+            };
+            self.report_key(*code, status);
+        }
+
+        for usage in last[2..]
+            .iter()
+            .filter(|usage| !report[2..].contains(usage))
+        {
+            self.report_usage(*usage, KeyStatus::Released);
+        }
+        for usage in report[2..]
+            .iter()
+            .filter(|usage| !last[2..].contains(usage))
+        {
+            self.report_usage(*usage, KeyStatus::Pressed);
+        }
+    }
+
+    fn report_usage(&self, usage: u8, status: KeyStatus) {
+        if let Some(code) = USAGE_KEYS.get(usage as usize) {
+            self.report_key(*code, status);
+        }
+    }
+
+    fn report_key(&self, code: u16, status: KeyStatus) {
+        // The keys that are not known by `aster-input` are dropped.
+        let Ok(key) = Key::try_from(code) else {
+            return;
+        };
+        if key == Key::Reserved {
+            return;
+        }
+        for callback in self.callbacks.read().iter() {
+            callback(InputEvent::KeyBoard(key, status));
+        }
+    }
+}
+
+impl InputDevice for UsbKeyboard {
+    fn register_callbacks(&self, function: &'static (dyn Fn(InputEvent) + Send + Sync)) {
+        self.callbacks.write().push(Arc::new(function))
+    }
+
+    fn name(&self) -> &str {
+        self.device.product().unwrap_or("USB Keyboard")
+    }
+
+    fn id(&self) -> InputId {
+        let descriptor = self.device.descriptor();
+        InputId {
+            bus_type: BUS_USB,
+            vendor: descriptor.vendor_id,
+            product: descriptor.product_id,
+            version: descriptor.device_version,
+        }
+    }
+}
+
+/// Polls the keyboards, which is called in the timer interrupts.
+fn poll_keyboards() {
+    for keyboard in KEYBOARDS.lock().iter() {
+        keyboard.device.host().poll();
+        keyboard.poll();
+    }
+}
@@ -0,0 +1,53 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The class drivers, which drive the interfaces of the devices.
+
+pub mod hid;
+pub mod storage;
+
+use alloc::{sync::Arc, vec::Vec};
+use core::fmt::Debug;
+
+use log::info;
+use ostd::sync::{LocalIrqDisabled, SpinLock};
+
+use crate::{descriptor::InterfaceDescriptor, device::UsbDevice};
+
+/// A driver of the USB interfaces.
+pub trait UsbDriver: Send + Sync + Debug {
+    /// Returns the name of the driver.
+    fn name(&self) -> &str;
+
+    /// Probes the interface of the device, returning whether the driver takes it.
+    ///
+    /// The driver may take the interface even if its initialization fails, so that the
+    /// interface is not passed to other drivers.
+    fn probe(&self, device: &Arc<UsbDevice>, interface: &InterfaceDescriptor) -> bool;
+}
+
+static DRIVERS: SpinLock<Vec<Arc<dyn UsbDriver>>, LocalIrqDisabled> = SpinLock::new(Vec::new());
+
+/// Registers a class driver.
+///
+/// The driver is only offered the interfaces of the devices enumerated later.
+pub fn register_driver(driver: Arc<dyn UsbDriver>) {
+    DRIVERS.lock().push(driver);
+}
+
+/// Passes each interface of the device to the drivers, until a driver takes it.
+pub(crate) fn bind_drivers(device: &Arc<UsbDevice>) {
+    let drivers = DRIVERS.lock().clone();
+    for interface in device.config().interfaces.iter() {
+        let driver = drivers
+            .iter()
+            .find(|driver| driver.probe(device, interface));
+        if let Some(driver) = driver {
+            info!(
+                "[USB]: {}: interface {} is driven by {}",
+                device.name(),
+                interface.number,
+                driver.name()
+            );
+        }
+    }
+}
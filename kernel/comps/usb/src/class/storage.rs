@@ -0,0 +1,475 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The USB mass storage class driver.
+//!
+//! Only the bulk-only transport (BBB) with the SCSI transparent command set is supported,
+//! which is used by almost all USB disks. Each command is wrapped in a command block wrapper
+//! (CBW) on the bulk-out endpoint, followed by the data stage and a command status wrapper
+//! (CSW) on the bulk-in endpoint. Only the first logical unit is used.
+//!
+//! Each disk is registered as a block device named `sdX` in `aster-block`.
+
+use alloc::{format, string::String, sync::Arc, vec, vec::Vec};
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+use aster_block::{
+    bio::{bio_segment_pool_init, BioEnqueueError, BioStatus, BioType, SubmittedBio},
+    request_queue::{BioRequest, BioRequestSingleQueue, QueueLimits},
+    BlockDeviceMeta, SECTOR_SIZE,
+};
+use log::{info, warn};
+use ostd::{
+    mm::{DmaDirection, DmaStream, DmaStreamSlice, FrameAllocOptions, VmIo},
+    sync::Mutex,
+};
+
+use super::UsbDriver;
+use crate::{
+    descriptor::{EndpointType, InterfaceDescriptor, RECIP_INTERFACE, TYPE_CLASS},
+    device::UsbDevice,
+    host::{delay_ms, UsbError},
+};
+
+// The class, the subclass, and the protocol of the supported interfaces.
+const CLASS_MASS_STORAGE: u8 = 0x08;
+const SUBCLASS_SCSI: u8 = 0x06;
+const PROTOCOL_BULK_ONLY: u8 = 0x50;
+
+// The class requests.
+const BULK_ONLY_RESET: u8 = 0xFF;
+
+const CBW_SIGNATURE: u32 = 0x4342_5355;
+const CBW_LEN: usize = 31;
+const CBW_FLAG_IN: u8 = 1 << 7;
+const CSW_SIGNATURE: u32 = 0x5342_5355;
+const CSW_LEN: usize = 13;
+/// The offset of the CSW in the command buffer.
+const CSW_OFFSET: usize = 64;
+/// The offset of the data of the small commands in the command buffer.
+const DATA_OFFSET: usize = 128;
+
+// The status of CSWs.
+const CSW_PASSED: u8 = 0;
+const CSW_FAILED: u8 = 1;
+
+// The SCSI operation codes.
+const TEST_UNIT_READY: u8 = 0x00;
+const REQUEST_SENSE: u8 = 0x03;
+const INQUIRY: u8 = 0x12;
+const READ_CAPACITY_10: u8 = 0x25;
+const READ_10: u8 = 0x28;
+const WRITE_10: u8 = 0x2A;
+const SYNCHRONIZE_CACHE_10: u8 = 0x35;
+
+/// The maximum number of sectors in a read or write command.
+const MAX_SECTORS_PER_COMMAND: usize = 240;
+/// The timeout for a command (in microseconds).
+const COMMAND_TIMEOUT_US: u64 = 20_000_000;
+/// The number of attempts to wait for the medium to be ready.
+const READY_RETRIES: usize = 20;
+
+/// The mass storage class driver.
+#[derive(Debug)]
+pub(crate) struct StorageDriver;
+
+impl UsbDriver for StorageDriver {
+    fn name(&self) -> &str {
+        "usb-storage"
+    }
+
+    fn probe(&self, device: &Arc<UsbDevice>, interface: &InterfaceDescriptor) -> bool {
+        if interface.class != CLASS_MASS_STORAGE
+            || interface.subclass != SUBCLASS_SCSI
+            || interface.protocol != PROTOCOL_BULK_ONLY
+        {
+            return false;
+        }
+
+        let disk = match UsbStorageBlockDevice::new(device.clone(), interface) {
+            Ok(disk) => disk,
+            Err(err) => {
+                warn!(
+                    "[USB]: {}: storage initialization failed: {:?}",
+                    device.name(),
+                    err
+                );
+                return true;
+            }
+        };
+
+        let index = NEXT_INDEX.fetch_add(1, Ordering::Relaxed);
+        if index >= 26 {
+            warn!("[USB]: {}: too many disks", device.name());
+            return true;
+        }
+        let name = format!("sd{}", (b'a' + index as u8) as char);
+        info!(
+            "[USB]: {}: {}, {} sectors, device {}",
+            name,
+            disk.model,
+            disk.nr_sectors,
+            device.name()
+        );
+        bio_segment_pool_init();
+        aster_block::register_device(name, Arc::new(disk));
+        true
+    }
+}
+
+/// The index of the next `sdX` device.
+static NEXT_INDEX: AtomicUsize = AtomicUsize::new(0);
+
+/// A USB disk exposed as a block device.
+///
+/// The requests are processed synchronously by [`UsbStorageBlockDevice::handle_requests`],
+/// which is expected to be called in a loop by a dedicated kernel thread.
+#[derive(Debug)]
+pub struct UsbStorageBlockDevice {
+    transport: Mutex<Transport>,
+    queue: BioRequestSingleQueue,
+    nr_sectors: usize,
+    model: String,
+}
+
+/// The bulk-only transport.
+#[derive(Debug)]
+struct Transport {
+    device: Arc<UsbDevice>,
+    interface: u8,
+    bulk_in: u8,
+    bulk_out: u8,
+    /// The buffer of the CBWs, the CSWs, and the data of the small commands.
+    buffer: DmaStream,
+    tag: AtomicU32,
+}
+
+/// The data stage of a command.
+enum DataStage<'a> {
+    None,
+    In(&'a DmaStreamSlice<DmaStream>),
+    Out(&'a DmaStreamSlice<DmaStream>),
+}
+
+impl UsbStorageBlockDevice {
+    fn new(device: Arc<UsbDevice>, interface: &InterfaceDescriptor) -> Result<Self, UsbError> {
+        let (Some(bulk_in), Some(bulk_out)) = (
+            interface.find_endpoint(EndpointType::Bulk, true),
+            interface.find_endpoint(EndpointType::Bulk, false),
+        ) else {
+            return Err(UsbError::InvalidDescriptor);
+        };
+        let buffer = {
+            let segment = FrameAllocOptions::new()
+                .alloc_segment(1)
+                .map_err(|_| UsbError::NoResource)?;
+            DmaStream::map(segment.into(), DmaDirection::Bidirectional, false)
+                .map_err(|_| UsbError::NoResource)?
+        };
+        let transport = Transport {
+            device,
+            interface: interface.number,
+            bulk_in: bulk_in.address,
+            bulk_out: bulk_out.address,
+            buffer,
+            tag: AtomicU32::new(1),
+        };
+
+        let inquiry = transport.small_command(&[INQUIRY, 0, 0, 0, 36, 0], 36)?;
+        if inquiry.len() < 36 {
+            return Err(UsbError::Transaction);
+        }
+        let model = core::str::from_utf8(&inquiry[8..32])
+            .unwrap_or("?")
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        transport.wait_until_ready()?;
+        let capacity =
+            transport.small_command(&[READ_CAPACITY_10, 0, 0, 0, 0, 0, 0, 0, 0, 0], 8)?;
+        if capacity.len() < 8 {
+            return Err(UsbError::Transaction);
+        }
+        let last_lba = u32::from_be_bytes(capacity[0..4].try_into().unwrap());
+        let block_len = u32::from_be_bytes(capacity[4..8].try_into().unwrap());
+        if block_len as usize != SECTOR_SIZE {
+            warn!(
+                "[USB]: {}: the block size {} is not supported",
+                transport.device.name(),
+                block_len
+            );
+            return Err(UsbError::Unsupported);
+        }
+        if last_lba == u32::MAX {
+            warn!(
+                "[USB]: {}: only the first 2 TiB can be accessed",
+                transport.device.name()
+            );
+        }
+
+        let limits = QueueLimits {
+            max_nr_sectors: MAX_SECTORS_PER_COMMAND,
+            ..QueueLimits::default()
+        };
+        Ok(Self {
+            transport: Mutex::new(transport),
+            queue: BioRequestSingleQueue::with_limits(limits),
+            nr_sectors: last_lba as usize + 1,
+            model,
+        })
+    }
+
+    /// Dequeues a request and processes it.
+    ///
+    /// This method blocks if there is no pending request.
+    pub fn handle_requests(&self) {
+        let request = self.queue.dequeue();
+        match request.type_() {
+            BioType::Read | BioType::Write => self.do_rw(request),
+            BioType::Flush => {
+                let transport = self.transport.lock();
+                let cdb = [SYNCHRONIZE_CACHE_10, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+                // Many disks do not support the command, and they do not cache the writes.
+                let _ = transport.command(&cdb, DataStage::None);
+                complete_all(&request, BioStatus::Complete);
+            }
+            BioType::Discard => complete_all(&request, BioStatus::NotSupported),
+        }
+    }
+
+    fn do_rw(&self, request: BioRequest) {
+        let transport = self.transport.lock();
+        let is_read = request.type_() == BioType::Read;
+
+        for bio in request.bios() {
+            let mut sector = bio.sid_range().start.to_raw() as usize;
+            let mut result = Ok(());
+
+            for segment in bio.segments() {
+                let dma_slice = segment.inner_dma_slice();
+                let nr_sectors = dma_slice.nbytes() / SECTOR_SIZE;
+                dma_slice.sync().unwrap();
+                result = transport.read_write(sector, nr_sectors, dma_slice, is_read);
+                if is_read {
+                    dma_slice.sync().unwrap();
+                }
+                if result.is_err() {
+                    break;
+                }
+                sector += nr_sectors;
+            }
+
+            let status = match result {
+                Ok(()) => BioStatus::Complete,
+                Err(err) => {
+                    warn!(
+                        "[USB]: {:?} failed at sector {}: {:?}",
+                        bio.type_(),
+                        sector,
+                        err
+                    );
+                    BioStatus::IoError
+                }
+            };
+            bio.complete(status);
+        }
+    }
+}
+
+fn complete_all(request: &BioRequest, status: BioStatus) {
+    for bio in request.bios() {
+        bio.complete(status);
+    }
+}
+
+impl aster_block::BlockDevice for UsbStorageBlockDevice {
+    fn enqueue(&self, bio: SubmittedBio) -> Result<(), BioEnqueueError> {
+        self.queue.enqueue(bio)
+    }
+
+    fn metadata(&self) -> BlockDeviceMeta {
+        BlockDeviceMeta {
+            max_nr_segments_per_bio: self.queue.max_nr_segments_per_bio(),
+            nr_sectors: self.nr_sectors,
+        }
+    }
+
+    fn plug(&self) {
+        self.queue.plug();
+    }
+
+    fn unplug(&self) {
+        self.queue.unplug();
+    }
+}
+
+impl Transport {
+    /// Reads or writes the sectors, splitting the transfer into multiple commands if needed.
+    fn read_write(
+        &self,
+        sector: usize,
+        nr_sectors: usize,
+        buffer: &DmaStreamSlice<DmaStream>,
+        is_read: bool,
+    ) -> Result<(), UsbError> {
+        let mut done = 0;
+        while done < nr_sectors {
+            let count = (nr_sectors - done).min(MAX_SECTORS_PER_COMMAND);
+            let lba = u32::try_from(sector + done).map_err(|_| UsbError::Unsupported)?;
+            let opcode = if is_read { READ_10 } else { WRITE_10 };
+            let lba = lba.to_be_bytes();
+            let len = (count as u16).to_be_bytes();
+            let cdb = [
+                opcode, 0, lba[0], lba[1], lba[2], lba[3], 0, len[0], len[1], 0,
+            ];
+
+            let slice = DmaStreamSlice::new(
+                buffer.stream().clone(),
+                buffer.offset() + done * SECTOR_SIZE,
+                count * SECTOR_SIZE,
+            );
+            let data = if is_read {
+                DataStage::In(&slice)
+            } else {
+                DataStage::Out(&slice)
+            };
+            let transferred = self.command(&cdb, data)?;
+            if transferred != count * SECTOR_SIZE {
+                return Err(UsbError::Transaction);
+            }
+            done += count;
+        }
+        Ok(())
+    }
+
+    /// Executes a command that reads up to `len` bytes into the command buffer.
+    fn small_command(&self, cdb: &[u8], len: usize) -> Result<Vec<u8>, UsbError> {
+        let slice = DmaStreamSlice::new(self.buffer.clone(), DATA_OFFSET, len);
+        slice.sync().unwrap();
+        let transferred = self.command(cdb, DataStage::In(&slice))?;
+        slice.sync().unwrap();
+
+        let mut bytes = vec![0u8; transferred.min(len)];
+        slice.read_bytes(0, &mut bytes).unwrap();
+        Ok(bytes)
+    }
+
+    /// Waits for the medium to be ready, e.g., after the disk spins up.
+    fn wait_until_ready(&self) -> Result<(), UsbError> {
+        for _ in 0..READY_RETRIES {
+            match self.command(&[TEST_UNIT_READY, 0, 0, 0, 0, 0], DataStage::None) {
+                Ok(_) => return Ok(()),
+                Err(UsbError::Io) => {
+                    // Read the sense data to clear the unit attention condition.
+                    let _ = self.small_command(&[REQUEST_SENSE, 0, 0, 0, 18, 0], 18);
+                    delay_ms(100);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Err(UsbError::Timeout)
+    }
+
+    /// Executes a command, returning the number of the bytes transferred in the data stage.
+    ///
+    /// If the command fails, [`UsbError::Io`] is returned.
+    fn command(&self, cdb: &[u8], data: DataStage) -> Result<usize, UsbError> {
+        let result = self.try_command(cdb, &data);
+        if matches!(
+            result,
+            Err(UsbError::Transaction | UsbError::Timeout | UsbError::Stall)
+        ) {
+            self.reset_recovery();
+        }
+        result
+    }
+
+    fn try_command(&self, cdb: &[u8], data: &DataStage) -> Result<usize, UsbError> {
+        let tag = self.tag.fetch_add(1, Ordering::Relaxed);
+        let (data_len, flags) = match data {
+            DataStage::None => (0, 0),
+            DataStage::In(buffer) => (buffer.nbytes(), CBW_FLAG_IN),
+            DataStage::Out(buffer) => (buffer.nbytes(), 0),
+        };
+
+        let mut cbw = [0u8; CBW_LEN];
+        cbw[0..4].copy_from_slice(&CBW_SIGNATURE.to_le_bytes());
+        cbw[4..8].copy_from_slice(&tag.to_le_bytes());
+        cbw[8..12].copy_from_slice(&(data_len as u32).to_le_bytes());
+        cbw[12] = flags;
+        cbw[14] = cdb.len() as u8;
+        cbw[15..15 + cdb.len()].copy_from_slice(cdb);
+        self.buffer.write_bytes(0, &cbw).unwrap();
+        let cbw_slice = DmaStreamSlice::new(self.buffer.clone(), 0, CBW_LEN);
+        cbw_slice.sync().unwrap();
+        self.device
+            .transfer(self.bulk_out, cbw_slice, COMMAND_TIMEOUT_US)?;
+
+        let transferred = match data {
+            DataStage::None => Ok(0),
+            DataStage::In(buffer) => {
+                self.device
+                    .transfer(self.bulk_in, (*buffer).clone(), COMMAND_TIMEOUT_US)
+            }
+            DataStage::Out(buffer) => {
+                self.device
+                    .transfer(self.bulk_out, (*buffer).clone(), COMMAND_TIMEOUT_US)
+            }
+        };
+        let transferred = match transferred {
+            Ok(transferred) => transferred,
+            // The device stalls the data stage if it transfers less data than expected.
+            Err(UsbError::Stall) => {
+                let endpoint = match data {
+                    DataStage::In(_) => self.bulk_in,
+                    _ => self.bulk_out,
+                };
+                self.device.clear_halt(endpoint)?;
+                0
+            }
+            Err(err) => return Err(err),
+        };
+
+        let csw_slice = DmaStreamSlice::new(self.buffer.clone(), CSW_OFFSET, CSW_LEN);
+        csw_slice.sync().unwrap();
+        let result = self
+            .device
+            .transfer(self.bulk_in, csw_slice.clone(), COMMAND_TIMEOUT_US);
+        if result == Err(UsbError::Stall) {
+            // Retry once after the endpoint is cleared.
+            self.device.clear_halt(self.bulk_in)?;
+            self.device
+                .transfer(self.bulk_in, csw_slice.clone(), COMMAND_TIMEOUT_US)?;
+        } else {
+            result?;
+        }
+        csw_slice.sync().unwrap();
+
+        let mut csw = [0u8; CSW_LEN];
+        csw_slice.read_bytes(0, &mut csw).unwrap();
+        let signature = u32::from_le_bytes(csw[0..4].try_into().unwrap());
+        let csw_tag = u32::from_le_bytes(csw[4..8].try_into().unwrap());
+        if signature != CSW_SIGNATURE || csw_tag != tag {
+            return Err(UsbError::Transaction);
+        }
+        match csw[12] {
+            CSW_PASSED => Ok(transferred),
+            CSW_FAILED => Err(UsbError::Io),
+            // A phase error, which requires the reset recovery.
+            _ => Err(UsbError::Transaction),
+        }
+    }
+
+    /// Resets the device after a transport error.
+    fn reset_recovery(&self) {
+        let _ = self.device.control_out(
+            TYPE_CLASS | RECIP_INTERFACE,
+            BULK_ONLY_RESET,
+            0,
+            self.interface as u16,
+            &[],
+        );
+        let _ = self.device.clear_halt(self.bulk_in);
+        let _ = self.device.clear_halt(self.bulk_out);
+    }
+}
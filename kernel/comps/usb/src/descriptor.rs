@@ -0,0 +1,262 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The standard requests and descriptors of the USB 2.0/3.x specifications.
+
+use alloc::vec::Vec;
+
+use crate::UsbError;
+
+// The bits of `bmRequestType`.
+pub const DIR_IN: u8 = 1 << 7;
+pub const TYPE_STANDARD: u8 = 0;
+pub const TYPE_CLASS: u8 = 1 << 5;
+pub const RECIP_DEVICE: u8 = 0;
+pub const RECIP_INTERFACE: u8 = 1;
+pub const RECIP_ENDPOINT: u8 = 2;
+pub const RECIP_OTHER: u8 = 3;
+
+// The standard requests.
+pub const GET_STATUS: u8 = 0x00;
+pub const CLEAR_FEATURE: u8 = 0x01;
+pub const SET_FEATURE: u8 = 0x03;
+pub const GET_DESCRIPTOR: u8 = 0x06;
+pub const SET_CONFIGURATION: u8 = 0x09;
+
+// The descriptor types.
+pub const DESC_DEVICE: u8 = 0x01;
+pub const DESC_CONFIGURATION: u8 = 0x02;
+pub const DESC_STRING: u8 = 0x03;
+pub const DESC_INTERFACE: u8 = 0x04;
+pub const DESC_ENDPOINT: u8 = 0x05;
+
+/// The feature selector of `ENDPOINT_HALT`.
+pub const FEATURE_ENDPOINT_HALT: u16 = 0;
+
+/// The setup packet of a control transfer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SetupPacket {
+    pub request_type: u8,
+    pub request: u8,
+    pub value: u16,
+    pub index: u16,
+    pub length: u16,
+}
+
+impl SetupPacket {
+    /// Returns whether the data stage moves the data from the device to the host.
+    pub fn is_in(&self) -> bool {
+        self.request_type & DIR_IN != 0
+    }
+
+    /// Returns the packet in the wire format, i.e., as a little-endian 64-bit integer.
+    pub fn to_u64(&self) -> u64 {
+        (self.request_type as u64)
+            | (self.request as u64) << 8
+            | (self.value as u64) << 16
+            | (self.index as u64) << 32
+            | (self.length as u64) << 48
+    }
+}
+
+/// The device descriptor.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DeviceDescriptor {
+    pub usb_version: u16,
+    pub class: u8,
+    pub subclass: u8,
+    pub protocol: u8,
+    pub max_packet_size0: u8,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub device_version: u16,
+    pub manufacturer_index: u8,
+    pub product_index: u8,
+    pub serial_index: u8,
+    pub nr_configurations: u8,
+}
+
+impl DeviceDescriptor {
+    /// The length of the descriptor.
+    pub const LEN: usize = 18;
+
+    /// Parses the descriptor.
+    ///
+    /// Only the first 8 bytes are required, which are enough for the maximum packet size
+    /// of the default control endpoint.
+    pub fn parse(bytes: &[u8]) -> Result<Self, UsbError> {
+        if bytes.len() < 8 || bytes[1] != DESC_DEVICE {
+            return Err(UsbError::InvalidDescriptor);
+        }
+        let mut desc = Self {
+            usb_version: read_u16(bytes, 2),
+            class: bytes[4],
+            subclass: bytes[5],
+            protocol: bytes[6],
+            max_packet_size0: bytes[7],
+            ..Self::default()
+        };
+        if bytes.len() >= Self::LEN {
+            desc.vendor_id = read_u16(bytes, 8);
+            desc.product_id = read_u16(bytes, 10);
+            desc.device_version = read_u16(bytes, 12);
+            desc.manufacturer_index = bytes[14];
+            desc.product_index = bytes[15];
+            desc.serial_index = bytes[16];
+            desc.nr_configurations = bytes[17];
+        }
+        Ok(desc)
+    }
+}
+
+/// The configuration descriptor, with the descriptors of its interfaces.
+#[derive(Debug, Clone)]
+pub struct ConfigDescriptor {
+    pub total_length: u16,
+    pub value: u8,
+    pub attributes: u8,
+    /// The maximum power consumption (in 2 mA units for USB 2.0 devices).
+    pub max_power: u8,
+    pub interfaces: Vec<InterfaceDescriptor>,
+}
+
+impl ConfigDescriptor {
+    /// The length of the descriptor without the following descriptors.
+    pub const LEN: usize = 9;
+
+    /// Parses the configuration descriptor and the descriptors following it.
+    ///
+    /// Only the default alternate settings of the interfaces are kept, and the unknown
+    /// descriptors (e.g., the class-specific ones) are skipped.
+    pub fn parse(bytes: &[u8]) -> Result<Self, UsbError> {
+        if bytes.len() < Self::LEN || bytes[1] != DESC_CONFIGURATION {
+            return Err(UsbError::InvalidDescriptor);
+        }
+        let mut config = Self {
+            total_length: read_u16(bytes, 2),
+            value: bytes[5],
+            attributes: bytes[7],
+            max_power: bytes[8],
+            interfaces: Vec::new(),
+        };
+
+        let end = bytes.len().min(config.total_length as usize);
+        let mut offset = bytes[0] as usize;
+        let mut in_default_setting = false;
+        while offset + 2 <= end {
+            let len = bytes[offset] as usize;
+            if len < 2 || offset + len > end {
+                return Err(UsbError::InvalidDescriptor);
+            }
+            let desc = &bytes[offset..offset + len];
+            match desc[1] {
+                DESC_INTERFACE if len >= 9 => {
+                    in_default_setting = desc[3] == 0;
+                    if in_default_setting {
+                        config.interfaces.push(InterfaceDescriptor {
+                            number: desc[2],
+                            class: desc[5],
+                            subclass: desc[6],
+                            protocol: desc[7],
+                            endpoints: Vec::new(),
+                        });
+                    }
+                }
+                DESC_ENDPOINT if len >= 7 && in_default_setting => {
+                    if let Some(interface) = config.interfaces.last_mut() {
+                        interface.endpoints.push(EndpointDescriptor {
+                            address: desc[2],
+                            attributes: desc[3],
+                            max_packet_size: read_u16(desc, 4),
+                            interval: desc[6],
+                        });
+                    }
+                }
+                _ => {}
+            }
+            offset += len;
+        }
+        Ok(config)
+    }
+}
+
+/// The interface descriptor, with the descriptors of its endpoints.
+#[derive(Debug, Clone)]
+pub struct InterfaceDescriptor {
+    pub number: u8,
+    pub class: u8,
+    pub subclass: u8,
+    pub protocol: u8,
+    pub endpoints: Vec<EndpointDescriptor>,
+}
+
+impl InterfaceDescriptor {
+    /// Finds the first endpoint with the transfer type and the direction.
+    pub fn find_endpoint(&self, type_: EndpointType, is_in: bool) -> Option<&EndpointDescriptor> {
+        self.endpoints
+            .iter()
+            .find(|ep| ep.type_() == type_ && ep.is_in() == is_in)
+    }
+}
+
+/// The endpoint descriptor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EndpointDescriptor {
+    /// The endpoint number in bits 3..0, and the direction in bit 7.
+    pub address: u8,
+    pub attributes: u8,
+    /// The maximum packet size in bits 10..0, and the additional transactions per
+    /// microframe of high-speed periodic endpoints in bits 12..11.
+    pub max_packet_size: u16,
+    pub interval: u8,
+}
+
+impl EndpointDescriptor {
+    /// Returns the endpoint number.
+    pub fn number(&self) -> u8 {
+        self.address & 0x0F
+    }
+
+    /// Returns whether the endpoint moves the data from the device to the host.
+    pub fn is_in(&self) -> bool {
+        self.address & DIR_IN != 0
+    }
+
+    /// Returns the transfer type of the endpoint.
+    pub fn type_(&self) -> EndpointType {
+        match self.attributes & 0b11 {
+            0 => EndpointType::Control,
+            1 => EndpointType::Isochronous,
+            2 => EndpointType::Bulk,
+            _ => EndpointType::Interrupt,
+        }
+    }
+}
+
+/// The transfer type of an endpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndpointType {
+    Control,
+    Isochronous,
+    Bulk,
+    Interrupt,
+}
+
+/// Decodes a string descriptor, replacing the non-ASCII characters.
+pub fn parse_string(bytes: &[u8]) -> Option<alloc::string::String> {
+    if bytes.len() < 2 || bytes[1] != DESC_STRING {
+        return None;
+    }
+    let len = (bytes[0] as usize).min(bytes.len());
+    let string = bytes[2..len]
+        .chunks_exact(2)
+        .map(|unit| match u16::from_le_bytes([unit[0], unit[1]]) {
+            code @ 0x20..=0x7E => code as u8 as char,
+            _ => '?',
+        })
+        .collect();
+    Some(string)
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
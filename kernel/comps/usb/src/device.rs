@@ -0,0 +1,312 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! USB devices and their enumeration.
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+
+use ostd::{
+    mm::{DmaDirection, DmaStream, DmaStreamSlice, FrameAllocOptions, VmIo, PAGE_SIZE},
+    sync::Mutex,
+};
+
+use crate::{
+    descriptor::*,
+    host::{DeviceRoute, SlotId, Urb, UsbError, UsbHostController, UsbSpeed},
+};
+
+/// The timeout for a control transfer (in microseconds).
+const CONTROL_TIMEOUT_US: u64 = 5_000_000;
+
+/// The language ID of US English, in which the strings are read.
+const LANG_ID_EN_US: u16 = 0x0409;
+
+/// A USB device, which is addressed and configured.
+#[derive(Debug)]
+pub struct UsbDevice {
+    name: String,
+    host: Arc<dyn UsbHostController>,
+    bus: usize,
+    slot: SlotId,
+    route: DeviceRoute,
+    descriptor: DeviceDescriptor,
+    config: ConfigDescriptor,
+    product: Option<String>,
+    /// The buffer of the data stages of the control transfers.
+    control_buffer: Mutex<DmaStream>,
+}
+
+impl UsbDevice {
+    /// Addresses and configures the device at `route`.
+    ///
+    /// The first configuration is chosen, which is the only one of most devices.
+    pub(crate) fn enumerate(
+        host: Arc<dyn UsbHostController>,
+        bus: usize,
+        route: DeviceRoute,
+    ) -> Result<Arc<Self>, UsbError> {
+        let slot = host.address_device(&route)?;
+        let control_buffer = {
+            let segment = FrameAllocOptions::new()
+                .alloc_segment(1)
+                .map_err(|_| UsbError::NoResource);
+            segment.and_then(|segment| {
+                DmaStream::map(segment.into(), DmaDirection::Bidirectional, false)
+                    .map_err(|_| UsbError::NoResource)
+            })
+        };
+        let control_buffer = match control_buffer {
+            Ok(buffer) => buffer,
+            Err(err) => {
+                host.disable_device(slot);
+                return Err(err);
+            }
+        };
+
+        let mut device = Self {
+            name: device_name(bus, &route),
+            host,
+            bus,
+            slot,
+            route,
+            descriptor: DeviceDescriptor::default(),
+            config: ConfigDescriptor {
+                total_length: 0,
+                value: 0,
+                attributes: 0,
+                max_power: 0,
+                interfaces: Vec::new(),
+            },
+            product: None,
+            control_buffer: Mutex::new(control_buffer),
+        };
+        // The device slot is released when the device is dropped on errors.
+        device.init()?;
+        Ok(Arc::new(device))
+    }
+
+    fn init(&mut self) -> Result<(), UsbError> {
+        // The maximum packet size, which is needed to read the whole descriptor, is in the
+        // first 8 bytes.
+        let bytes = self.get_descriptor(DESC_DEVICE, 0, 8)?;
+        let descriptor = DeviceDescriptor::parse(&bytes)?;
+        let max_packet_size0 = match self.route.speed {
+            // The size of SuperSpeed devices is given by the exponent.
+            UsbSpeed::Super | UsbSpeed::SuperPlus => 1 << descriptor.max_packet_size0.min(9),
+            _ => descriptor.max_packet_size0 as u16,
+        };
+        if max_packet_size0 != self.route.speed.default_max_packet_size0() {
+            self.host
+                .set_max_packet_size0(self.slot, max_packet_size0)?;
+        }
+
+        let bytes = self.get_descriptor(DESC_DEVICE, 0, DeviceDescriptor::LEN)?;
+        self.descriptor = DeviceDescriptor::parse(&bytes)?;
+        if self.descriptor.nr_configurations == 0 {
+            return Err(UsbError::InvalidDescriptor);
+        }
+
+        let bytes = self.get_descriptor(DESC_CONFIGURATION, 0, ConfigDescriptor::LEN)?;
+        let total_length = ConfigDescriptor::parse(&bytes)?.total_length as usize;
+        let bytes = self.get_descriptor(DESC_CONFIGURATION, 0, total_length.min(PAGE_SIZE))?;
+        self.config = ConfigDescriptor::parse(&bytes)?;
+
+        if self.descriptor.product_index != 0 {
+            self.product = self.read_string(self.descriptor.product_index).ok();
+        }
+
+        let endpoints: Vec<_> = self
+            .config
+            .interfaces
+            .iter()
+            .flat_map(|interface| interface.endpoints.iter().copied())
+            .collect();
+        self.host.configure_endpoints(self.slot, &endpoints)?;
+        self.control_out(
+            TYPE_STANDARD | RECIP_DEVICE,
+            SET_CONFIGURATION,
+            self.config.value as u16,
+            0,
+            &[],
+        )
+    }
+
+    /// Returns the name of the device, e.g., `1-2.3` for the device on port 3 of the hub on
+    /// port 2 of the root hub of bus 1.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the host controller of the device.
+    pub fn host(&self) -> &Arc<dyn UsbHostController> {
+        &self.host
+    }
+
+    /// Returns the bus number, i.e., the index of the host controller starting from 1.
+    pub fn bus(&self) -> usize {
+        self.bus
+    }
+
+    /// Returns the slot of the device in the host controller.
+    pub fn slot(&self) -> SlotId {
+        self.slot
+    }
+
+    pub fn route(&self) -> &DeviceRoute {
+        &self.route
+    }
+
+    pub fn speed(&self) -> UsbSpeed {
+        self.route.speed
+    }
+
+    pub fn descriptor(&self) -> &DeviceDescriptor {
+        &self.descriptor
+    }
+
+    /// Returns the active configuration.
+    pub fn config(&self) -> &ConfigDescriptor {
+        &self.config
+    }
+
+    /// Returns the product string of the device.
+    pub fn product(&self) -> Option<&str> {
+        self.product.as_deref()
+    }
+
+    /// Performs a control transfer that reads up to `len` bytes from the device.
+    pub fn control_in(
+        &self,
+        request_type: u8,
+        request: u8,
+        value: u16,
+        index: u16,
+        len: usize,
+    ) -> Result<Vec<u8>, UsbError> {
+        let setup = SetupPacket {
+            request_type: request_type | DIR_IN,
+            request,
+            value,
+            index,
+            length: len.min(PAGE_SIZE) as u16,
+        };
+        let buffer = self.control_buffer.lock();
+        let transferred = self.control(setup, &buffer)?;
+        buffer.sync(0..transferred).unwrap();
+
+        let mut bytes = vec![0u8; transferred];
+        buffer.read_bytes(0, &mut bytes).unwrap();
+        Ok(bytes)
+    }
+
+    /// Performs a control transfer that writes `data` to the device.
+    pub fn control_out(
+        &self,
+        request_type: u8,
+        request: u8,
+        value: u16,
+        index: u16,
+        data: &[u8],
+    ) -> Result<(), UsbError> {
+        if data.len() > PAGE_SIZE {
+            return Err(UsbError::Unsupported);
+        }
+        let setup = SetupPacket {
+            request_type: request_type & !DIR_IN,
+            request,
+            value,
+            index,
+            length: data.len() as u16,
+        };
+        let buffer = self.control_buffer.lock();
+        buffer.write_bytes(0, data).unwrap();
+        buffer.sync(0..data.len()).unwrap();
+        self.control(setup, &buffer).map(|_| ())
+    }
+
+    fn control(&self, setup: SetupPacket, buffer: &DmaStream) -> Result<usize, UsbError> {
+        let data = (setup.length > 0)
+            .then(|| DmaStreamSlice::new(buffer.clone(), 0, setup.length as usize));
+        let urb = Urb::control(setup, data);
+        self.host.submit(self.slot, &urb)?;
+        let result = urb.wait(self.host.as_ref(), self.slot, CONTROL_TIMEOUT_US);
+        if result == Err(UsbError::Stall) {
+            // A stall of the default control endpoint halts it only on the host side.
+            self.host.reset_endpoint(self.slot, 0)?;
+        }
+        result
+    }
+
+    /// Reads a descriptor of the device.
+    pub fn get_descriptor(&self, type_: u8, index: u8, len: usize) -> Result<Vec<u8>, UsbError> {
+        let language = if type_ == DESC_STRING && index != 0 {
+            LANG_ID_EN_US
+        } else {
+            0
+        };
+        self.control_in(
+            TYPE_STANDARD | RECIP_DEVICE,
+            GET_DESCRIPTOR,
+            (type_ as u16) << 8 | index as u16,
+            language,
+            len,
+        )
+    }
+
+    /// Reads a string descriptor of the device.
+    pub fn read_string(&self, index: u8) -> Result<String, UsbError> {
+        let bytes = self.get_descriptor(DESC_STRING, index, 255)?;
+        parse_string(&bytes)
+            .map(|string| string.trim().to_string())
+            .ok_or(UsbError::InvalidDescriptor)
+    }
+
+    /// Submits a bulk or interrupt transfer on the endpoint.
+    pub fn submit(&self, urb: &Arc<Urb>) -> Result<(), UsbError> {
+        self.host.submit(self.slot, urb)
+    }
+
+    /// Performs a bulk or interrupt transfer on the endpoint, waiting for its completion.
+    pub fn transfer(
+        &self,
+        endpoint: u8,
+        buffer: DmaStreamSlice<DmaStream>,
+        timeout_us: u64,
+    ) -> Result<usize, UsbError> {
+        let urb = Urb::new(endpoint, buffer);
+        self.host.submit(self.slot, &urb)?;
+        urb.wait(self.host.as_ref(), self.slot, timeout_us)
+    }
+
+    /// Clears the halt condition of the endpoint, on both the device and the host.
+    pub fn clear_halt(&self, endpoint: u8) -> Result<(), UsbError> {
+        self.control_out(
+            TYPE_STANDARD | RECIP_ENDPOINT,
+            CLEAR_FEATURE,
+            FEATURE_ENDPOINT_HALT,
+            endpoint as u16,
+            &[],
+        )?;
+        self.host.reset_endpoint(self.slot, endpoint)
+    }
+}
+
+impl Drop for UsbDevice {
+    fn drop(&mut self) {
+        self.host.disable_device(self.slot);
+    }
+}
+
+fn device_name(bus: usize, route: &DeviceRoute) -> String {
+    let mut name = format!("{}-{}", bus, route.root_port);
+    for tier in 0..route.depth {
+        let port = (route.route_string >> (tier * 4)) & 0xF;
+        name += &format!(".{}", port);
+    }
+    name
+}
@@ -0,0 +1,296 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! USB host controllers.
+
+pub mod xhci;
+
+use alloc::sync::Arc;
+use core::{fmt::Debug, hint::spin_loop};
+
+use ostd::{
+    mm::{DmaStream, DmaStreamSlice},
+    sync::{LocalIrqDisabled, SpinLock},
+};
+
+use crate::descriptor::{EndpointDescriptor, SetupPacket};
+
+/// A USB host controller.
+///
+/// Besides moving the data, the host controller manages the root hub and the device slots.
+/// The interface follows xHCI, where the controller assigns the device addresses and must
+/// be told about the endpoints and the hubs. The transfers are asynchronous: they are
+/// submitted as [`Urb`]s and completed by [`UsbHostController::poll`].
+pub trait UsbHostController: Send + Sync + Debug {
+    /// Returns the name of the host controller.
+    fn name(&self) -> &str;
+
+    /// Returns the number of the ports of the root hub.
+    ///
+    /// The ports are numbered from 1.
+    fn nr_ports(&self) -> u8;
+
+    /// Returns whether a device is connected to the root port.
+    fn port_connected(&self, port: u8) -> bool;
+
+    /// Resets and enables the root port, returning the speed of the connected device.
+    fn reset_port(&self, port: u8) -> Result<UsbSpeed, UsbError>;
+
+    /// Allocates a device slot for the device at `route`, and assigns an address to it.
+    ///
+    /// The default control endpoint is ready once this method returns.
+    fn address_device(&self, route: &DeviceRoute) -> Result<SlotId, UsbError>;
+
+    /// Updates the maximum packet size of the default control endpoint.
+    fn set_max_packet_size0(&self, slot: SlotId, max_packet_size: u16) -> Result<(), UsbError>;
+
+    /// Configures the endpoints of the device before it is configured.
+    fn configure_endpoints(
+        &self,
+        slot: SlotId,
+        endpoints: &[EndpointDescriptor],
+    ) -> Result<(), UsbError>;
+
+    /// Marks the device as a hub, so that the devices below it can be addressed.
+    fn set_hub(&self, slot: SlotId, hub: HubInfo) -> Result<(), UsbError>;
+
+    /// Submits a transfer to the device.
+    ///
+    /// The transfer is completed later by [`Self::poll`].
+    fn submit(&self, slot: SlotId, urb: &Arc<Urb>) -> Result<(), UsbError>;
+
+    /// Processes the completions of the transfers.
+    fn poll(&self);
+
+    /// Cancels the pending transfers on the endpoint.
+    ///
+    /// The transfers are completed with [`UsbError::Cancelled`].
+    fn cancel(&self, slot: SlotId, endpoint: u8);
+
+    /// Resets the endpoint after it is halted, e.g., by a stall.
+    ///
+    /// For other endpoints than the default control endpoint, the halt condition of the
+    /// device side must be cleared by `CLEAR_FEATURE(ENDPOINT_HALT)` as well.
+    fn reset_endpoint(&self, slot: SlotId, endpoint: u8) -> Result<(), UsbError>;
+
+    /// Releases the device slot.
+    fn disable_device(&self, slot: SlotId);
+}
+
+/// The ID of a device slot of a host controller.
+pub type SlotId = u8;
+
+/// The speed of a device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum UsbSpeed {
+    Low,
+    Full,
+    High,
+    Super,
+    SuperPlus,
+}
+
+impl UsbSpeed {
+    /// Returns the default maximum packet size of the default control endpoint.
+    ///
+    /// The actual size of full-speed devices is known after reading the device descriptor.
+    pub fn default_max_packet_size0(self) -> u16 {
+        match self {
+            UsbSpeed::Low | UsbSpeed::Full => 8,
+            UsbSpeed::High => 64,
+            UsbSpeed::Super | UsbSpeed::SuperPlus => 512,
+        }
+    }
+}
+
+/// The location of a device in the USB tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceRoute {
+    /// The root port, from which the device is reached.
+    pub root_port: u8,
+    /// The ports of the hubs below the root port, 4 bits per hub tier (xHCI route string).
+    pub route_string: u32,
+    /// The number of hubs between the device and the root hub.
+    pub depth: u8,
+    pub speed: UsbSpeed,
+    /// The slot and the port of the high-speed hub, whose transaction translator serves
+    /// the low/full-speed device.
+    pub tt: Option<(SlotId, u8)>,
+}
+
+impl DeviceRoute {
+    /// Returns the route of a device connected to `port` of the hub at this route.
+    pub fn child(&self, hub_slot: SlotId, port: u8, speed: UsbSpeed) -> Self {
+        let tt = match self.tt {
+            Some(tt) => Some(tt),
+            None if self.speed == UsbSpeed::High && speed < UsbSpeed::High => {
+                Some((hub_slot, port))
+            }
+            None => None,
+        };
+        Self {
+            root_port: self.root_port,
+            route_string: self.route_string | (port.min(15) as u32) << (self.depth * 4),
+            depth: self.depth + 1,
+            speed,
+            tt,
+        }
+    }
+}
+
+/// The properties of a hub that are needed by the host controller.
+#[derive(Debug, Clone, Copy)]
+pub struct HubInfo {
+    pub nr_ports: u8,
+    /// Whether the hub has a transaction translator per port.
+    pub multi_tt: bool,
+    /// The think time of the transaction translator (in 8 full-speed bit times, minus 1).
+    pub tt_think_time: u8,
+}
+
+/// A USB request block, i.e., a transfer on an endpoint of a device.
+#[derive(Debug)]
+pub struct Urb {
+    endpoint: u8,
+    setup: Option<SetupPacket>,
+    buffer: Option<DmaStreamSlice<DmaStream>>,
+    result: SpinLock<Option<Result<usize, UsbError>>, LocalIrqDisabled>,
+}
+
+impl Urb {
+    /// Creates a control transfer on the default control endpoint.
+    ///
+    /// The buffer must be at least `setup.length` bytes long if `setup.length` is not zero.
+    pub fn control(setup: SetupPacket, buffer: Option<DmaStreamSlice<DmaStream>>) -> Arc<Self> {
+        debug_assert!(
+            setup.length == 0 || buffer.as_ref().unwrap().nbytes() >= setup.length as usize
+        );
+        Arc::new(Self {
+            endpoint: 0,
+            setup: Some(setup),
+            buffer,
+            result: SpinLock::new(None),
+        })
+    }
+
+    /// Creates a bulk or interrupt transfer on the endpoint.
+    ///
+    /// The direction of the transfer is given by bit 7 of the endpoint address.
+    pub fn new(endpoint: u8, buffer: DmaStreamSlice<DmaStream>) -> Arc<Self> {
+        Arc::new(Self {
+            endpoint,
+            setup: None,
+            buffer: Some(buffer),
+            result: SpinLock::new(None),
+        })
+    }
+
+    /// Returns the endpoint address.
+    pub fn endpoint(&self) -> u8 {
+        self.endpoint
+    }
+
+    /// Returns the setup packet of a control transfer.
+    pub fn setup(&self) -> Option<&SetupPacket> {
+        self.setup.as_ref()
+    }
+
+    /// Returns the data buffer.
+    ///
+    /// The buffer is synchronized by the submitter before and after the transfer.
+    pub fn buffer(&self) -> Option<&DmaStreamSlice<DmaStream>> {
+        self.buffer.as_ref()
+    }
+
+    /// Returns the length of the data stage.
+    pub fn transfer_len(&self) -> usize {
+        match (&self.setup, &self.buffer) {
+            (Some(setup), _) => setup.length as usize,
+            (None, Some(buffer)) => buffer.nbytes(),
+            (None, None) => 0,
+        }
+    }
+
+    /// Returns the result (the number of bytes transferred), or `None` if it is pending.
+    pub fn result(&self) -> Option<Result<usize, UsbError>> {
+        *self.result.lock()
+    }
+
+    /// Completes the transfer. This is called by the host controller drivers.
+    pub fn complete(&self, result: Result<usize, UsbError>) {
+        let mut slot = self.result.lock();
+        if slot.is_none() {
+            *slot = Some(result);
+        }
+    }
+
+    /// Waits for the completion by polling the host controller.
+    ///
+    /// If the transfer is not completed in `timeout_us` microseconds, everything pending on
+    /// the endpoint is cancelled.
+    pub fn wait(
+        &self,
+        host: &dyn UsbHostController,
+        slot: SlotId,
+        timeout_us: u64,
+    ) -> Result<usize, UsbError> {
+        let result = poll_until(timeout_us, || {
+            host.poll();
+            self.result()
+        });
+        match result {
+            Ok(result) => result,
+            Err(err) => {
+                host.cancel(slot, self.endpoint);
+                self.complete(Err(err));
+                self.result().unwrap()
+            }
+        }
+    }
+}
+
+/// The errors of USB operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UsbError {
+    /// The device or the controller does not respond in time.
+    Timeout,
+    /// The endpoint is halted by the device.
+    Stall,
+    /// The data is corrupted on the bus, or the device transfers more data than expected.
+    Transaction,
+    /// The transfer is cancelled.
+    Cancelled,
+    /// The device is disconnected.
+    NoDevice,
+    /// The controller runs out of the device slots or the memory.
+    NoResource,
+    /// The device returns a malformed descriptor.
+    InvalidDescriptor,
+    /// The device or the request is not supported.
+    Unsupported,
+    /// The controller reports an error.
+    Io,
+}
+
+/// Polls `cond` until it returns `Some`, or until `timeout_us` microseconds elapse.
+pub(crate) fn poll_until<T>(
+    timeout_us: u64,
+    mut cond: impl FnMut() -> Option<T>,
+) -> Result<T, UsbError> {
+    let freq = ostd::arch::tsc_freq().max(1);
+    let deadline = ostd::arch::read_tsc() + timeout_us.saturating_mul(freq) / 1_000_000;
+    loop {
+        if let Some(value) = cond() {
+            return Ok(value);
+        }
+        if ostd::arch::read_tsc() > deadline {
+            // Check once more in case that we were preempted for a long time.
+            return cond().ok_or(UsbError::Timeout);
+        }
+        spin_loop();
+    }
+}
+
+/// Waits for at least `ms` milliseconds.
+pub(crate) fn delay_ms(ms: u64) {
+    let _ = poll_until::<()>(ms * 1000, || None);
+}
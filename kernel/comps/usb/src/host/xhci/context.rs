@@ -0,0 +1,249 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The device contexts and the input contexts.
+//!
+//! A device context holds a slot context and 31 endpoint contexts, which are indexed by the
+//! device context index (DCI). An input context is prefixed with an input control context.
+//! The size of each context is 32 or 64 bytes, depending on the controller.
+
+use ostd::mm::{DmaCoherent, VmIo};
+
+use crate::{
+    descriptor::{EndpointDescriptor, EndpointType},
+    host::{DeviceRoute, UsbSpeed},
+};
+
+// The endpoint types in the endpoint contexts.
+const EP_TYPE_ISOCH_OUT: u32 = 1;
+const EP_TYPE_BULK_OUT: u32 = 2;
+const EP_TYPE_INTERRUPT_OUT: u32 = 3;
+const EP_TYPE_CONTROL: u32 = 4;
+const EP_TYPE_ISOCH_IN: u32 = 5;
+const EP_TYPE_BULK_IN: u32 = 6;
+const EP_TYPE_INTERRUPT_IN: u32 = 7;
+
+// The endpoint states in the endpoint contexts.
+pub(super) const EP_STATE_HALTED: u32 = 2;
+pub(super) const EP_STATE_STOPPED: u32 = 3;
+
+// The bits of the slot contexts.
+const SLOT_MTT: u32 = 1 << 25;
+const SLOT_HUB: u32 = 1 << 26;
+
+/// The number of error retries of the non-isochronous endpoints.
+const CERR: u32 = 3;
+
+/// Returns the device context index of the endpoint address.
+pub(super) fn endpoint_dci(endpoint: u8) -> u8 {
+    match endpoint & 0x0F {
+        0 => 1,
+        number => number * 2 + (endpoint >> 7),
+    }
+}
+
+/// Returns the speed ID of the protocol speed.
+pub(super) fn speed_id(speed: UsbSpeed) -> u32 {
+    match speed {
+        UsbSpeed::Full => 1,
+        UsbSpeed::Low => 2,
+        UsbSpeed::High => 3,
+        UsbSpeed::Super => 4,
+        UsbSpeed::SuperPlus => 5,
+    }
+}
+
+/// A device context, which is written by the controller.
+#[derive(Debug)]
+pub(super) struct DeviceContext {
+    pub dma: DmaCoherent,
+    ctx_size: usize,
+}
+
+impl DeviceContext {
+    pub fn new(dma: DmaCoherent, ctx_size: usize) -> Self {
+        Self { dma, ctx_size }
+    }
+
+    /// Returns the state of the endpoint.
+    pub fn endpoint_state(&self, dci: u8) -> u32 {
+        let dword: u32 = self.dma.read_val(dci as usize * self.ctx_size).unwrap();
+        dword & 0b111
+    }
+
+    /// Reads the first 4 dwords of the slot context.
+    fn slot(&self) -> [u32; 4] {
+        self.dma.read_val(0).unwrap()
+    }
+}
+
+/// An input context, which passes the contexts to the controller in the commands.
+#[derive(Debug)]
+pub(super) struct InputContext {
+    pub dma: DmaCoherent,
+    ctx_size: usize,
+}
+
+impl InputContext {
+    pub fn new(dma: DmaCoherent, ctx_size: usize) -> Self {
+        Self { dma, ctx_size }
+    }
+
+    /// Clears the input control context, and sets the add context flags.
+    ///
+    /// Bit 0 is for the slot context, and bit N is for the endpoint context with DCI N.
+    pub fn set_add_flags(&self, flags: u32) {
+        self.dma.write_val(0, &[0u32, flags]).unwrap();
+    }
+
+    /// Writes the slot context for a device at `route`.
+    pub fn write_slot(&self, route: &DeviceRoute, tt_multi: bool, context_entries: u8) {
+        let mut dwords = [0u32; 8];
+        dwords[0] =
+            route.route_string | speed_id(route.speed) << 20 | (context_entries as u32) << 27;
+        if route.tt.is_some() && tt_multi {
+            dwords[0] |= SLOT_MTT;
+        }
+        dwords[1] = (route.root_port as u32) << 16;
+        if let Some((hub_slot, port)) = route.tt {
+            dwords[2] = hub_slot as u32 | (port as u32) << 8;
+        }
+        self.dma.write_val(self.ctx_size, &dwords).unwrap();
+    }
+
+    /// Copies the slot context from the device context, updating the context entries.
+    pub fn copy_slot(&self, output: &DeviceContext, context_entries: u8) {
+        let mut dwords = output.slot();
+        dwords[0] = (dwords[0] & !(0x1F << 27)) | (context_entries as u32) << 27;
+        // Clear the device address and the slot state.
+        dwords[3] = 0;
+        self.dma.write_val(self.ctx_size, &dwords).unwrap();
+    }
+
+    /// Marks the device as a hub in the slot context.
+    pub fn set_hub(&self, nr_ports: u8, multi_tt: bool, tt_think_time: Option<u8>) {
+        let offset = self.ctx_size;
+        let mut dwords: [u32; 3] = self.dma.read_val(offset).unwrap();
+        dwords[0] |= SLOT_HUB;
+        if multi_tt {
+            dwords[0] |= SLOT_MTT;
+        }
+        dwords[1] = (dwords[1] & 0x00FF_FFFF) | (nr_ports as u32) << 24;
+        if let Some(think_time) = tt_think_time {
+            dwords[2] = (dwords[2] & !(0b11 << 16)) | (think_time as u32 & 0b11) << 16;
+        }
+        self.dma.write_val(offset, &dwords).unwrap();
+    }
+
+    /// Writes the context of the default control endpoint.
+    pub fn write_ep0(&self, max_packet_size: u16, dequeue_pointer: u64) {
+        self.write_endpoint(
+            1,
+            0,
+            CERR << 1 | EP_TYPE_CONTROL << 3 | (max_packet_size as u32) << 16,
+            dequeue_pointer,
+            8,
+        );
+    }
+
+    /// Updates the maximum packet size of the default control endpoint.
+    pub fn set_ep0_max_packet_size(&self, max_packet_size: u16) {
+        let offset = self.endpoint_offset(1) + 4;
+        let dword: u32 = self.dma.read_val(offset).unwrap();
+        let dword = (dword & 0xFFFF) | (max_packet_size as u32) << 16;
+        self.dma.write_val(offset, &dword).unwrap();
+    }
+
+    /// Writes the context of an endpoint that is described by `desc`.
+    pub fn write_endpoint_desc(
+        &self,
+        desc: &EndpointDescriptor,
+        speed: UsbSpeed,
+        dequeue_pointer: u64,
+    ) {
+        let type_ = desc.type_();
+        let max_packet_size = (desc.max_packet_size & 0x7FF) as u32;
+        // High-speed periodic endpoints can do additional transactions per microframe.
+        let mult = match (speed, type_) {
+            (UsbSpeed::High, EndpointType::Interrupt | EndpointType::Isochronous) => {
+                ((desc.max_packet_size >> 11) & 0b11) as u32
+            }
+            _ => 0,
+        };
+        let ep_type = match (type_, desc.is_in()) {
+            (EndpointType::Control, _) => EP_TYPE_CONTROL,
+            (EndpointType::Isochronous, false) => EP_TYPE_ISOCH_OUT,
+            (EndpointType::Isochronous, true) => EP_TYPE_ISOCH_IN,
+            (EndpointType::Bulk, false) => EP_TYPE_BULK_OUT,
+            (EndpointType::Bulk, true) => EP_TYPE_BULK_IN,
+            (EndpointType::Interrupt, false) => EP_TYPE_INTERRUPT_OUT,
+            (EndpointType::Interrupt, true) => EP_TYPE_INTERRUPT_IN,
+        };
+        let cerr = if type_ == EndpointType::Isochronous {
+            0
+        } else {
+            CERR
+        };
+
+        let interval = interval_exponent(desc, speed);
+        let is_periodic = matches!(type_, EndpointType::Interrupt | EndpointType::Isochronous);
+        let (avg_trb_len, max_esit_payload) = if is_periodic {
+            let payload = max_packet_size * (mult + 1);
+            (payload, payload)
+        } else if type_ == EndpointType::Control {
+            (8, 0)
+        } else {
+            (3072, 0)
+        };
+
+        self.write_endpoint(
+            desc_dci(desc),
+            mult << 8 | interval << 16,
+            cerr << 1 | ep_type << 3 | max_packet_size << 16,
+            dequeue_pointer,
+            avg_trb_len | max_esit_payload << 16,
+        );
+    }
+
+    fn write_endpoint(&self, dci: u8, dword0: u32, dword1: u32, dequeue_pointer: u64, dword4: u32) {
+        let dwords = [
+            dword0,
+            dword1,
+            dequeue_pointer as u32,
+            (dequeue_pointer >> 32) as u32,
+            dword4,
+        ];
+        self.dma
+            .write_val(self.endpoint_offset(dci), &dwords)
+            .unwrap();
+    }
+
+    fn endpoint_offset(&self, dci: u8) -> usize {
+        // Skip the input control context.
+        (dci as usize + 1) * self.ctx_size
+    }
+}
+
+/// Returns the device context index of the endpoint.
+pub(super) fn desc_dci(desc: &EndpointDescriptor) -> u8 {
+    if desc.type_() == EndpointType::Control {
+        return desc.number() * 2 + 1;
+    }
+    endpoint_dci(desc.address)
+}
+
+/// Converts `bInterval` to the interval in the endpoint context, whose period is
+/// `2^interval * 125` microseconds.
+fn interval_exponent(desc: &EndpointDescriptor, speed: UsbSpeed) -> u32 {
+    let interval = desc.interval as u32;
+    match (desc.type_(), speed) {
+        (EndpointType::Control | EndpointType::Bulk, _) => 0,
+        // The full-speed isochronous endpoints use the exponent in frames.
+        (EndpointType::Isochronous, UsbSpeed::Full) => interval.clamp(1, 16) + 2,
+        // The low/full-speed interrupt endpoints use the period in frames.
+        (EndpointType::Interrupt, UsbSpeed::Low | UsbSpeed::Full) => {
+            (interval.max(1) * 8).ilog2().clamp(3, 10)
+        }
+        // The high-speed and the SuperSpeed endpoints use the exponent in microframes.
+        _ => interval.clamp(1, 16) - 1,
+    }
+}
@@ -0,0 +1,895 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The eXtensible Host Controller Interface (xHCI) driver.
+//!
+//! The driver uses one command ring, one event ring with a single segment, and one transfer
+//! ring per endpoint. All the rings and the contexts are in coherent DMA mappings, while the
+//! data buffers are provided by the users of the transfers in streaming DMA mappings.
+//!
+//! The interrupts are not enabled. The event ring is polled by `UsbHostController::poll`, which
+//! completes the commands and the transfers.
+//!
+//! Isochronous transfers, streams, and USB 3 link power management are not supported.
+//!
+//! Reference: eXtensible Host Controller Interface for Universal Serial Bus, revision 1.2.
+
+mod context;
+mod ring;
+
+use alloc::{collections::BTreeMap, string::String, sync::Arc, vec::Vec};
+
+use ostd::{
+    io::MmioRegion,
+    mm::{Daddr, DmaCoherent, FrameAllocOptions, HasDaddr, VmIo, VmIoOnce, PAGE_SIZE},
+    sync::{LocalIrqDisabled, SpinLock},
+};
+
+use self::{
+    context::{
+        desc_dci, endpoint_dci, DeviceContext, InputContext, EP_STATE_HALTED, EP_STATE_STOPPED,
+    },
+    ring::*,
+};
+use super::{poll_until, DeviceRoute, HubInfo, SlotId, Urb, UsbError, UsbHostController, UsbSpeed};
+use crate::descriptor::{EndpointDescriptor, EndpointType};
+
+/// The capability register offsets.
+mod cap_regs {
+    pub const CAPLENGTH: usize = 0x00;
+    pub const HCIVERSION: usize = 0x02;
+    pub const HCSPARAMS1: usize = 0x04;
+    pub const HCSPARAMS2: usize = 0x08;
+    pub const HCCPARAMS1: usize = 0x10;
+    pub const DBOFF: usize = 0x14;
+    pub const RTSOFF: usize = 0x18;
+}
+
+/// The operational register offsets.
+mod op_regs {
+    pub const USBCMD: usize = 0x00;
+    pub const USBSTS: usize = 0x04;
+    pub const PAGESIZE: usize = 0x08;
+    pub const CRCR: usize = 0x18;
+    pub const DCBAAP: usize = 0x30;
+    pub const CONFIG: usize = 0x38;
+    pub const PORTSC: usize = 0x400;
+}
+
+/// The register offsets of an interrupter in the runtime registers.
+mod intr_regs {
+    pub const IR0: usize = 0x20;
+    pub const IMAN: usize = 0x00;
+    pub const ERSTSZ: usize = 0x08;
+    pub const ERSTBA: usize = 0x10;
+    pub const ERDP: usize = 0x18;
+}
+
+// The bits of the HCCPARAMS1 register.
+const HCC_AC64: u32 = 1 << 0;
+const HCC_CSZ: u32 = 1 << 2;
+const HCC_PPC: u32 = 1 << 3;
+
+// The bits of the USBCMD register.
+const CMD_RUN: u32 = 1 << 0;
+const CMD_RESET: u32 = 1 << 1;
+
+// The bits of the USBSTS register.
+const STS_HALTED: u32 = 1 << 0;
+const STS_NOT_READY: u32 = 1 << 11;
+
+// The bits of the PORTSC registers.
+const PORT_CONNECT: u32 = 1 << 0;
+const PORT_ENABLE: u32 = 1 << 1;
+const PORT_RESET: u32 = 1 << 4;
+const PORT_POWER: u32 = 1 << 9;
+const PORT_SPEED_SHIFT: u32 = 10;
+const PORT_CHANGE_MASK: u32 = 0x7F << 17;
+const PORT_RESET_CHANGE: u32 = 1 << 21;
+/// The bits that are not changed by writing their current values to the register.
+///
+/// The others are either "write 1 to clear" bits or are changed by writing 1s.
+const PORT_PRESERVE_MASK: u32 = 0x0E00_C3E9;
+
+// The bits of the ERDP register.
+const ERDP_BUSY: u64 = 1 << 3;
+
+/// The extended capability ID of the USB legacy support.
+const EXT_CAP_LEGACY: u32 = 1;
+// The bits of the USB legacy support capability.
+const LEGACY_BIOS_OWNED: u32 = 1 << 16;
+const LEGACY_OS_OWNED: u32 = 1 << 24;
+
+/// The timeout for resetting or starting the controller (in microseconds).
+const RESET_TIMEOUT_US: u64 = 1_000_000;
+/// The timeout for a command (in microseconds).
+const COMMAND_TIMEOUT_US: u64 = 5_000_000;
+/// The timeout for resetting a port (in microseconds).
+const PORT_RESET_TIMEOUT_US: u64 = 500_000;
+
+/// The maximum length of the data of a TRB, which must not cross a 64 KiB boundary.
+const TRB_MAX_LEN: usize = 0x10000;
+
+/// An xHCI host controller.
+#[derive(Debug)]
+pub struct Xhci {
+    name: String,
+    io_mem: MmioRegion,
+    op_base: usize,
+    runtime_base: usize,
+    doorbell_base: usize,
+    nr_ports: u8,
+    /// The size of the contexts (32 or 64 bytes).
+    ctx_size: usize,
+    is_64bit: bool,
+    /// The device context base address array.
+    dcbaa: DmaCoherent,
+    /// The scratchpad buffer array and the buffers, which are owned by the controller.
+    _scratchpad: Option<(DmaCoherent, DmaCoherent)>,
+    state: SpinLock<XhciState, LocalIrqDisabled>,
+}
+
+#[derive(Debug)]
+struct XhciState {
+    command_ring: Ring,
+    event_ring: EventRing,
+    /// The completion events of the commands, indexed by the addresses of the command TRBs.
+    command_events: BTreeMap<Daddr, Trb>,
+    slots: BTreeMap<SlotId, Slot>,
+}
+
+#[derive(Debug)]
+struct Slot {
+    input: InputContext,
+    output: DeviceContext,
+    route: DeviceRoute,
+    is_multi_tt_hub: bool,
+    /// The endpoints, indexed by the device context indices.
+    endpoints: BTreeMap<u8, Endpoint>,
+}
+
+#[derive(Debug)]
+struct Endpoint {
+    ring: Ring,
+    pending: Vec<TransferDescriptor>,
+}
+
+/// A transfer that is submitted to a transfer ring.
+#[derive(Debug)]
+struct TransferDescriptor {
+    urb: Arc<Urb>,
+    /// The addresses, the offsets in the data, and the lengths of the TRBs with data.
+    data_trbs: Vec<(Daddr, usize, usize)>,
+    /// The address of the last TRB, which interrupts on completion.
+    last_trb: Daddr,
+    nr_trbs: usize,
+    is_control: bool,
+    transferred: usize,
+}
+
+impl Xhci {
+    /// Resets and starts the controller.
+    pub fn new(name: String, io_mem: MmioRegion) -> Result<Self, UsbError> {
+        let cap_length: u8 = io_mem.read_once(cap_regs::CAPLENGTH).unwrap();
+        let version: u16 = io_mem.read_once(cap_regs::HCIVERSION).unwrap();
+        let hcs_params1: u32 = io_mem.read_once(cap_regs::HCSPARAMS1).unwrap();
+        let hcs_params2: u32 = io_mem.read_once(cap_regs::HCSPARAMS2).unwrap();
+        let hcc_params1: u32 = io_mem.read_once(cap_regs::HCCPARAMS1).unwrap();
+        let doorbell_base: u32 = io_mem.read_once(cap_regs::DBOFF).unwrap();
+        let runtime_base: u32 = io_mem.read_once(cap_regs::RTSOFF).unwrap();
+
+        let max_slots = (hcs_params1 & 0xFF) as u8;
+        let nr_ports = (hcs_params1 >> 24) as u8;
+        let nr_scratchpads =
+            (((hcs_params2 >> 21) & 0x1F) << 5 | (hcs_params2 >> 27) & 0x1F) as usize;
+        let is_64bit = hcc_params1 & HCC_AC64 != 0;
+
+        let op_base = cap_length as usize;
+        let page_size: u32 = io_mem.read_once(op_base + op_regs::PAGESIZE).unwrap();
+        if page_size & 1 == 0 {
+            log::warn!(
+                "[xHCI]: {}: the controller does not support 4 KiB pages",
+                name
+            );
+            return Err(UsbError::Unsupported);
+        }
+
+        take_ownership(&name, &io_mem, hcc_params1);
+
+        // Stop and reset the controller.
+        let read_op = |offset: usize| -> u32 { io_mem.read_once(op_base + offset).unwrap() };
+        let write_op = |offset: usize, value: u32| {
+            io_mem.write_once(op_base + offset, &value).unwrap();
+        };
+        write_op(op_regs::USBCMD, read_op(op_regs::USBCMD) & !CMD_RUN);
+        poll_until(RESET_TIMEOUT_US, || {
+            (read_op(op_regs::USBSTS) & STS_HALTED != 0).then_some(())
+        })?;
+        write_op(op_regs::USBCMD, CMD_RESET);
+        poll_until(RESET_TIMEOUT_US, || {
+            let is_done = read_op(op_regs::USBCMD) & CMD_RESET == 0
+                && read_op(op_regs::USBSTS) & STS_NOT_READY == 0;
+            is_done.then_some(())
+        })?;
+
+        let dcbaa = alloc_dma(1, is_64bit)?;
+        let scratchpad = if nr_scratchpads > 0 {
+            let array = alloc_dma(1, is_64bit)?;
+            let buffers = alloc_dma(nr_scratchpads, is_64bit)?;
+            for index in 0..nr_scratchpads {
+                let daddr = (buffers.daddr() + index * PAGE_SIZE) as u64;
+                array.write_val(index * 8, &daddr).unwrap();
+            }
+            dcbaa.write_val(0, &(array.daddr() as u64)).unwrap();
+            Some((array, buffers))
+        } else {
+            None
+        };
+
+        let xhci = Self {
+            name,
+            op_base,
+            runtime_base: (runtime_base & !0x1F) as usize,
+            doorbell_base: (doorbell_base & !0x3) as usize,
+            nr_ports,
+            ctx_size: if hcc_params1 & HCC_CSZ != 0 { 64 } else { 32 },
+            is_64bit,
+            dcbaa,
+            _scratchpad: scratchpad,
+            state: SpinLock::new(XhciState {
+                command_ring: Ring::new(is_64bit)?,
+                event_ring: EventRing::new(is_64bit)?,
+                command_events: BTreeMap::new(),
+                slots: BTreeMap::new(),
+            }),
+            io_mem,
+        };
+
+        xhci.write_op(op_regs::CONFIG, max_slots as u32);
+        xhci.write_op64(op_regs::DCBAAP, xhci.dcbaa.daddr() as u64);
+        {
+            let state = xhci.state.lock();
+            xhci.write_op64(op_regs::CRCR, state.command_ring.dequeue_pointer());
+
+            // We poll the event ring, so the interrupter is not enabled.
+            let intr = xhci.runtime_base + intr_regs::IR0;
+            xhci.write32(intr + intr_regs::IMAN, 0);
+            xhci.write32(intr + intr_regs::ERSTSZ, 1);
+            xhci.write64(
+                intr + intr_regs::ERDP,
+                state.event_ring.dequeue_daddr() as u64,
+            );
+            xhci.write64(
+                intr + intr_regs::ERSTBA,
+                state.event_ring.segment_table_daddr() as u64,
+            );
+        }
+
+        xhci.write_op(op_regs::USBCMD, CMD_RUN);
+        poll_until(RESET_TIMEOUT_US, || {
+            (xhci.read_op(op_regs::USBSTS) & STS_HALTED == 0).then_some(())
+        })?;
+
+        if hcc_params1 & HCC_PPC != 0 {
+            for port in 1..=nr_ports {
+                let portsc = xhci.read_portsc(port);
+                xhci.write_portsc(port, (portsc & PORT_PRESERVE_MASK) | PORT_POWER);
+            }
+        }
+        // Wait for the power to be good.
+        super::delay_ms(20);
+
+        log::info!(
+            "[xHCI]: {}: version {:x}.{:02x}, {} ports, {} slots, 64-bit DMA: {}",
+            xhci.name,
+            version >> 8,
+            version & 0xFF,
+            nr_ports,
+            max_slots,
+            is_64bit
+        );
+        Ok(xhci)
+    }
+
+    fn read32(&self, offset: usize) -> u32 {
+        self.io_mem.read_once(offset).unwrap()
+    }
+
+    fn write32(&self, offset: usize, value: u32) {
+        self.io_mem.write_once(offset, &value).unwrap();
+    }
+
+    /// Writes a 64-bit register by two 32-bit writes, which are supported by all controllers.
+    fn write64(&self, offset: usize, value: u64) {
+        self.write32(offset, value as u32);
+        self.write32(offset + 4, (value >> 32) as u32);
+    }
+
+    fn read_op(&self, offset: usize) -> u32 {
+        self.read32(self.op_base + offset)
+    }
+
+    fn write_op(&self, offset: usize, value: u32) {
+        self.write32(self.op_base + offset, value);
+    }
+
+    fn write_op64(&self, offset: usize, value: u64) {
+        self.write64(self.op_base + offset, value);
+    }
+
+    fn read_portsc(&self, port: u8) -> u32 {
+        self.read_op(op_regs::PORTSC + (port as usize - 1) * 0x10)
+    }
+
+    fn write_portsc(&self, port: u8, value: u32) {
+        self.write_op(op_regs::PORTSC + (port as usize - 1) * 0x10, value);
+    }
+
+    fn ring_doorbell(&self, slot: SlotId, target: u8) {
+        self.write32(self.doorbell_base + slot as usize * 4, target as u32);
+    }
+
+    /// Processes the events, with the state locked.
+    fn process_events(&self, state: &mut XhciState) {
+        let mut has_events = false;
+        while let Some(event) = state.event_ring.pop() {
+            has_events = true;
+            match event.type_() {
+                TRB_COMMAND_COMPLETION => {
+                    state.command_events.insert(event.parameter as Daddr, event);
+                }
+                TRB_TRANSFER_EVENT => {
+                    let endpoint = state
+                        .slots
+                        .get_mut(&event.slot_id())
+                        .and_then(|slot| slot.endpoints.get_mut(&event.endpoint_id()));
+                    if let Some(endpoint) = endpoint {
+                        endpoint.handle_event(&event);
+                    }
+                }
+                // The port status changes are checked when the ports are reset.
+                _ => {}
+            }
+        }
+
+        if has_events {
+            let erdp = self.runtime_base + intr_regs::IR0 + intr_regs::ERDP;
+            self.write64(erdp, state.event_ring.dequeue_daddr() as u64 | ERDP_BUSY);
+        }
+    }
+
+    /// Executes a command, returning its completion event.
+    fn command(&self, trb: Trb) -> Result<Trb, UsbError> {
+        let daddr = {
+            let mut state = self.state.lock();
+            if !state.command_ring.has_room(1) {
+                return Err(UsbError::NoResource);
+            }
+            let daddr = state.command_ring.push(trb);
+            self.ring_doorbell(0, 0);
+            daddr
+        };
+
+        let event = poll_until(COMMAND_TIMEOUT_US, || {
+            let mut state = self.state.lock();
+            self.process_events(&mut state);
+            let event = state.command_events.remove(&daddr)?;
+            state.command_ring.release(1);
+            Some(event)
+        })
+        .inspect_err(|_| log::warn!("[xHCI]: {}: command {} timed out", self.name, trb.type_()))?;
+        event.result().map(|_| event)
+    }
+
+    /// Modifies the input context of the slot and executes a command with it.
+    fn context_command(
+        &self,
+        slot: SlotId,
+        type_: u32,
+        modify: impl FnOnce(&InputContext, &DeviceContext),
+    ) -> Result<(), UsbError> {
+        let input_daddr = {
+            let state = self.state.lock();
+            let slot = state.slots.get(&slot).ok_or(UsbError::NoDevice)?;
+            modify(&slot.input, &slot.output);
+            slot.input.dma.daddr() as u64
+        };
+        self.command(Trb::new(type_, input_daddr, 0, (slot as u32) << 24))
+            .map(|_| ())
+    }
+
+    /// Moves the dequeue pointer of the endpoint to its enqueue pointer, and completes the
+    /// pending transfers with `error`.
+    fn skip_pending(&self, slot: SlotId, dci: u8, error: UsbError) -> Result<(), UsbError> {
+        let dequeue_pointer = {
+            let state = self.state.lock();
+            let endpoint = state
+                .slots
+                .get(&slot)
+                .and_then(|slot| slot.endpoints.get(&dci))
+                .ok_or(UsbError::NoDevice)?;
+            endpoint.ring.dequeue_pointer()
+        };
+        let result = self.command(Trb::new(
+            TRB_SET_TR_DEQUEUE,
+            dequeue_pointer,
+            0,
+            (slot as u32) << 24 | (dci as u32) << 16,
+        ));
+
+        let mut state = self.state.lock();
+        if let Some(endpoint) = state
+            .slots
+            .get_mut(&slot)
+            .and_then(|slot| slot.endpoints.get_mut(&dci))
+        {
+            for td in endpoint.pending.drain(..) {
+                td.urb.complete(Err(error));
+            }
+            endpoint.ring.release_all();
+        }
+        result.map(|_| ())
+    }
+
+    fn endpoint_state(&self, slot: SlotId, dci: u8) -> Option<u32> {
+        let state = self.state.lock();
+        let slot = state.slots.get(&slot)?;
+        Some(slot.output.endpoint_state(dci))
+    }
+}
+
+impl Endpoint {
+    fn new(is_64bit: bool) -> Result<Self, UsbError> {
+        Ok(Self {
+            ring: Ring::new(is_64bit)?,
+            pending: Vec::new(),
+        })
+    }
+
+    /// Handles a transfer event of the endpoint.
+    fn handle_event(&mut self, event: &Trb) {
+        let trb = event.parameter as Daddr;
+        let Some(index) = self
+            .pending
+            .iter()
+            .position(|td| td.last_trb == trb || td.data_trbs.iter().any(|(d, ..)| *d == trb))
+        else {
+            // The transfer is already completed by a short packet or is cancelled.
+            return;
+        };
+        let td = &mut self.pending[index];
+
+        let result = event.result();
+        let is_done = match result {
+            Err(UsbError::Cancelled) => return,
+            Err(_) => true,
+            Ok(()) => {
+                if event.completion_code() == CC_SHORT_PACKET {
+                    if let Some((_, offset, len)) = td.data_trbs.iter().find(|(d, ..)| *d == trb) {
+                        td.transferred = offset + len.saturating_sub(event.residual_len());
+                    }
+                }
+                // The rest of a bulk or interrupt transfer is skipped after a short packet,
+                // while the status stage of a control transfer is still executed.
+                trb == td.last_trb || (event.completion_code() == CC_SHORT_PACKET && !td.is_control)
+            }
+        };
+        if !is_done {
+            return;
+        }
+
+        let td = self.pending.remove(index);
+        self.ring.release(td.nr_trbs);
+        td.urb.complete(result.map(|_| td.transferred));
+    }
+}
+
+impl UsbHostController for Xhci {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn nr_ports(&self) -> u8 {
+        self.nr_ports
+    }
+
+    fn port_connected(&self, port: u8) -> bool {
+        self.read_portsc(port) & PORT_CONNECT != 0
+    }
+
+    fn reset_port(&self, port: u8) -> Result<UsbSpeed, UsbError> {
+        let portsc = self.read_portsc(port);
+        if portsc & PORT_CONNECT == 0 {
+            return Err(UsbError::NoDevice);
+        }
+        // USB 3 ports are enabled once the link is trained, so they are not reset.
+        if portsc & PORT_ENABLE == 0 {
+            self.write_portsc(port, (portsc & PORT_PRESERVE_MASK) | PORT_RESET);
+            poll_until(PORT_RESET_TIMEOUT_US, || {
+                (self.read_portsc(port) & PORT_RESET_CHANGE != 0).then_some(())
+            })?;
+        }
+
+        // Clear the change bits.
+        let portsc = self.read_portsc(port);
+        self.write_portsc(
+            port,
+            (portsc & PORT_PRESERVE_MASK) | (portsc & PORT_CHANGE_MASK),
+        );
+        if portsc & PORT_ENABLE == 0 {
+            return Err(UsbError::Io);
+        }
+        // The reset recovery time.
+        super::delay_ms(10);
+
+        match (portsc >> PORT_SPEED_SHIFT) & 0xF {
+            1 => Ok(UsbSpeed::Full),
+            2 => Ok(UsbSpeed::Low),
+            3 => Ok(UsbSpeed::High),
+            4 => Ok(UsbSpeed::Super),
+            5 => Ok(UsbSpeed::SuperPlus),
+            _ => Err(UsbError::Unsupported),
+        }
+    }
+
+    fn address_device(&self, route: &DeviceRoute) -> Result<SlotId, UsbError> {
+        let event = self.command(Trb::new(TRB_ENABLE_SLOT, 0, 0, 0))?;
+        let slot_id = event.slot_id();
+
+        let init_slot = || -> Result<Slot, UsbError> {
+            let input = InputContext::new(alloc_dma(1, self.is_64bit)?, self.ctx_size);
+            let output = DeviceContext::new(alloc_dma(1, self.is_64bit)?, self.ctx_size);
+            let ep0 = Endpoint::new(self.is_64bit)?;
+
+            let state = self.state.lock();
+            let tt_multi = route
+                .tt
+                .and_then(|(hub_slot, _)| state.slots.get(&hub_slot))
+                .is_some_and(|hub| hub.is_multi_tt_hub);
+            input.set_add_flags(0b11);
+            input.write_slot(route, tt_multi, 1);
+            input.write_ep0(
+                route.speed.default_max_packet_size0(),
+                ep0.ring.dequeue_pointer(),
+            );
+            Ok(Slot {
+                input,
+                output,
+                route: *route,
+                is_multi_tt_hub: false,
+                endpoints: BTreeMap::from([(1, ep0)]),
+            })
+        };
+        let slot = match init_slot() {
+            Ok(slot) => slot,
+            Err(err) => {
+                let _ = self.command(Trb::new(TRB_DISABLE_SLOT, 0, 0, (slot_id as u32) << 24));
+                return Err(err);
+            }
+        };
+
+        self.dcbaa
+            .write_val(slot_id as usize * 8, &(slot.output.dma.daddr() as u64))
+            .unwrap();
+        let input_daddr = slot.input.dma.daddr() as u64;
+        self.state.lock().slots.insert(slot_id, slot);
+
+        let result = self.command(Trb::new(
+            TRB_ADDRESS_DEVICE,
+            input_daddr,
+            0,
+            (slot_id as u32) << 24,
+        ));
+        if let Err(err) = result {
+            self.disable_device(slot_id);
+            return Err(err);
+        }
+        Ok(slot_id)
+    }
+
+    fn set_max_packet_size0(&self, slot: SlotId, max_packet_size: u16) -> Result<(), UsbError> {
+        self.context_command(slot, TRB_EVALUATE_CONTEXT, |input, _| {
+            input.set_add_flags(1 << 1);
+            input.set_ep0_max_packet_size(max_packet_size);
+        })
+    }
+
+    fn configure_endpoints(
+        &self,
+        slot: SlotId,
+        endpoints: &[EndpointDescriptor],
+    ) -> Result<(), UsbError> {
+        let endpoints: Vec<_> = endpoints
+            .iter()
+            .filter(|desc| desc.type_() != EndpointType::Isochronous)
+            .collect();
+        let mut rings = Vec::new();
+        for desc in endpoints.iter() {
+            rings.push((desc_dci(desc), Endpoint::new(self.is_64bit)?));
+        }
+        let Some(max_dci) = rings.iter().map(|(dci, _)| *dci).max() else {
+            return Ok(());
+        };
+
+        let speed = {
+            let state = self.state.lock();
+            state
+                .slots
+                .get(&slot)
+                .ok_or(UsbError::NoDevice)?
+                .route
+                .speed
+        };
+        self.context_command(slot, TRB_CONFIGURE_ENDPOINT, |input, output| {
+            let mut add_flags = 1;
+            for (desc, (dci, endpoint)) in endpoints.iter().zip(rings.iter()) {
+                input.write_endpoint_desc(desc, speed, endpoint.ring.dequeue_pointer());
+                add_flags |= 1 << dci;
+            }
+            input.set_add_flags(add_flags);
+            input.copy_slot(output, max_dci);
+        })?;
+
+        let mut state = self.state.lock();
+        let slot = state.slots.get_mut(&slot).ok_or(UsbError::NoDevice)?;
+        slot.endpoints.extend(rings);
+        Ok(())
+    }
+
+    fn set_hub(&self, slot: SlotId, hub: HubInfo) -> Result<(), UsbError> {
+        self.context_command(slot, TRB_CONFIGURE_ENDPOINT, |input, output| {
+            let speed = output_speed(output);
+            input.set_add_flags(1);
+            let context_entries = output_context_entries(output);
+            input.copy_slot(output, context_entries);
+            let tt_think_time = (speed == Some(UsbSpeed::High)).then_some(hub.tt_think_time);
+            input.set_hub(hub.nr_ports, hub.multi_tt, tt_think_time);
+        })?;
+
+        let mut state = self.state.lock();
+        if let Some(slot) = state.slots.get_mut(&slot) {
+            slot.is_multi_tt_hub = hub.multi_tt;
+        }
+        Ok(())
+    }
+
+    fn submit(&self, slot_id: SlotId, urb: &Arc<Urb>) -> Result<(), UsbError> {
+        let mut state = self.state.lock();
+        let dci = endpoint_dci(urb.endpoint());
+        let endpoint = state
+            .slots
+            .get_mut(&slot_id)
+            .and_then(|slot| slot.endpoints.get_mut(&dci))
+            .ok_or(UsbError::NoDevice)?;
+
+        let len = urb.transfer_len();
+        let data_daddr = urb.buffer().map(|buffer| buffer.daddr());
+        if let Some(daddr) = data_daddr {
+            if !self.is_64bit && (daddr + len) as u64 > u32::MAX as u64 {
+                return Err(UsbError::Unsupported);
+            }
+        }
+
+        // Split the data at the 64 KiB boundaries.
+        let mut chunks = Vec::new();
+        if let Some(daddr) = data_daddr {
+            let mut offset = 0;
+            while offset < len {
+                let chunk_len = (TRB_MAX_LEN - (daddr + offset) % TRB_MAX_LEN).min(len - offset);
+                chunks.push((daddr + offset, offset, chunk_len));
+                offset += chunk_len;
+            }
+        }
+
+        let mut td = TransferDescriptor {
+            urb: urb.clone(),
+            data_trbs: Vec::with_capacity(chunks.len()),
+            last_trb: 0,
+            nr_trbs: 0,
+            is_control: urb.setup().is_some(),
+            transferred: len,
+        };
+
+        let mut trbs = Vec::with_capacity(chunks.len() + 2);
+        if let Some(setup) = urb.setup() {
+            let transfer_type = match (len, setup.is_in()) {
+                (0, _) => 0,
+                (_, false) => 2,
+                (_, true) => 3,
+            };
+            trbs.push(Trb::new(
+                TRB_SETUP,
+                setup.to_u64(),
+                8,
+                TRB_IDT | transfer_type << 16,
+            ));
+            let dir_in = if setup.is_in() { TRB_DIR_IN } else { 0 };
+            for (index, (daddr, _, chunk_len)) in chunks.iter().enumerate() {
+                // The data stage starts with a data stage TRB, which may be chained with
+                // normal TRBs.
+                let (type_, dir) = if index == 0 {
+                    (TRB_DATA, dir_in)
+                } else {
+                    (TRB_NORMAL, 0)
+                };
+                let chain = if index + 1 < chunks.len() {
+                    TRB_CHAIN
+                } else {
+                    0
+                };
+                trbs.push(Trb::new(
+                    type_,
+                    *daddr as u64,
+                    *chunk_len as u32,
+                    TRB_ISP | chain | dir,
+                ));
+            }
+            // The status stage is in the opposite direction of the data stage.
+            let status_dir = if len == 0 || !setup.is_in() {
+                TRB_DIR_IN
+            } else {
+                0
+            };
+            trbs.push(Trb::new(TRB_STATUS, 0, 0, TRB_IOC | status_dir));
+        } else {
+            for (index, (daddr, _, chunk_len)) in chunks.iter().enumerate() {
+                let flags = if index + 1 < chunks.len() {
+                    TRB_CHAIN
+                } else {
+                    TRB_IOC
+                };
+                trbs.push(Trb::new(
+                    TRB_NORMAL,
+                    *daddr as u64,
+                    *chunk_len as u32,
+                    TRB_ISP | flags,
+                ));
+            }
+            if trbs.is_empty() {
+                trbs.push(Trb::new(TRB_NORMAL, 0, 0, TRB_IOC));
+            }
+        }
+
+        if !endpoint.ring.has_room(trbs.len()) {
+            return Err(UsbError::NoResource);
+        }
+        let data_start = usize::from(td.is_control);
+        for (index, trb) in trbs.iter().enumerate() {
+            let daddr = endpoint.ring.push(*trb);
+            if index >= data_start && index - data_start < chunks.len() {
+                let (_, offset, chunk_len) = chunks[index - data_start];
+                td.data_trbs.push((daddr, offset, chunk_len));
+            }
+            td.last_trb = daddr;
+        }
+        td.nr_trbs = trbs.len();
+        endpoint.pending.push(td);
+
+        self.ring_doorbell(slot_id, dci);
+        Ok(())
+    }
+
+    fn poll(&self) {
+        let mut state = self.state.lock();
+        self.process_events(&mut state);
+    }
+
+    fn cancel(&self, slot: SlotId, endpoint: u8) {
+        let dci = endpoint_dci(endpoint);
+        let Some(ep_state) = self.endpoint_state(slot, dci) else {
+            return;
+        };
+        if ep_state == EP_STATE_HALTED {
+            let _ = self.command(Trb::new(
+                TRB_RESET_ENDPOINT,
+                0,
+                0,
+                (slot as u32) << 24 | (dci as u32) << 16,
+            ));
+        } else if ep_state != EP_STATE_STOPPED {
+            let _ = self.command(Trb::new(
+                TRB_STOP_ENDPOINT,
+                0,
+                0,
+                (slot as u32) << 24 | (dci as u32) << 16,
+            ));
+        }
+        if let Err(err) = self.skip_pending(slot, dci, UsbError::Cancelled) {
+            log::warn!(
+                "[xHCI]: {}: failed to cancel the transfers of slot {} endpoint {}: {:?}",
+                self.name,
+                slot,
+                dci,
+                err
+            );
+        }
+    }
+
+    fn reset_endpoint(&self, slot: SlotId, endpoint: u8) -> Result<(), UsbError> {
+        let dci = endpoint_dci(endpoint);
+        if self.endpoint_state(slot, dci) != Some(EP_STATE_HALTED) {
+            return Ok(());
+        }
+        self.command(Trb::new(
+            TRB_RESET_ENDPOINT,
+            0,
+            0,
+            (slot as u32) << 24 | (dci as u32) << 16,
+        ))?;
+        // The rest of the transfer that caused the halt is still on the ring, so skip it.
+        self.skip_pending(slot, dci, UsbError::Cancelled)
+    }
+
+    fn disable_device(&self, slot: SlotId) {
+        let _ = self.command(Trb::new(TRB_DISABLE_SLOT, 0, 0, (slot as u32) << 24));
+        self.dcbaa.write_val(slot as usize * 8, &0u64).unwrap();
+        if let Some(slot) = self.state.lock().slots.remove(&slot) {
+            for endpoint in slot.endpoints.into_values() {
+                for td in endpoint.pending {
+                    td.urb.complete(Err(UsbError::NoDevice));
+                }
+            }
+        }
+    }
+}
+
+/// Returns the speed of the device in the device context.
+fn output_speed(output: &DeviceContext) -> Option<UsbSpeed> {
+    let dword: u32 = output.dma.read_val(0).unwrap();
+    match (dword >> 20) & 0xF {
+        1 => Some(UsbSpeed::Full),
+        2 => Some(UsbSpeed::Low),
+        3 => Some(UsbSpeed::High),
+        4 => Some(UsbSpeed::Super),
+        5 => Some(UsbSpeed::SuperPlus),
+        _ => None,
+    }
+}
+
+/// Returns the context entries of the device context.
+fn output_context_entries(output: &DeviceContext) -> u8 {
+    let dword: u32 = output.dma.read_val(0).unwrap();
+    (dword >> 27) as u8
+}
+
+/// Takes the ownership of the controller from the firmware, if the firmware owns it.
+fn take_ownership(name: &str, io_mem: &MmioRegion, hcc_params1: u32) {
+    let mut offset = ((hcc_params1 >> 16) as usize) << 2;
+    while offset != 0 {
+        let cap: u32 = io_mem.read_once(offset).unwrap();
+        if cap & 0xFF == EXT_CAP_LEGACY {
+            io_mem.write_once(offset, &(cap | LEGACY_OS_OWNED)).unwrap();
+            let result = poll_until(RESET_TIMEOUT_US, || {
+                let cap: u32 = io_mem.read_once(offset).unwrap();
+                (cap & LEGACY_BIOS_OWNED == 0).then_some(())
+            });
+            if result.is_err() {
+                log::warn!(
+                    "[xHCI]: {}: the firmware does not release the controller",
+                    name
+                );
+            }
+            return;
+        }
+
+        let next = ((cap >> 8) & 0xFF) as usize;
+        if next == 0 {
+            return;
+        }
+        offset += next << 2;
+    }
+}
+
+/// Allocates zeroed frames in a coherent DMA mapping.
+///
+/// The mapping is uncached, since the controller may not snoop the caches. Since the controller may only access
+/// the lower 4 GiB, the allocation fails if the frames are above that and `is_64bit` is false.
+pub(super) fn alloc_dma(nframes: usize, is_64bit: bool) -> Result<DmaCoherent, UsbError> {
+    let segment = FrameAllocOptions::new()
+        .alloc_segment(nframes)
+        .map_err(|_| UsbError::NoResource)?;
+    let dma = DmaCoherent::map(segment.into(), false).map_err(|_| UsbError::NoResource)?;
+    if !is_64bit && (dma.daddr() + nframes * PAGE_SIZE) as u64 > u32::MAX as u64 {
+        return Err(UsbError::NoResource);
+    }
+    Ok(dma)
+}
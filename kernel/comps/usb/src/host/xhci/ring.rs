@@ -0,0 +1,252 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The transfer request blocks (TRBs) and the rings of them.
+
+use core::sync::atomic::{fence, Ordering};
+
+use ostd::mm::{Daddr, DmaCoherent, HasDaddr, VmIo, VmIoOnce, PAGE_SIZE};
+
+use super::alloc_dma;
+use crate::UsbError;
+
+/// The size of a TRB.
+const TRB_SIZE: usize = 16;
+/// The number of TRBs in a ring (or in the event ring segment), which occupies one page.
+const RING_LEN: usize = PAGE_SIZE / TRB_SIZE;
+
+// The TRB types.
+pub(super) const TRB_NORMAL: u32 = 1;
+pub(super) const TRB_SETUP: u32 = 2;
+pub(super) const TRB_DATA: u32 = 3;
+pub(super) const TRB_STATUS: u32 = 4;
+const TRB_LINK: u32 = 6;
+pub(super) const TRB_ENABLE_SLOT: u32 = 9;
+pub(super) const TRB_DISABLE_SLOT: u32 = 10;
+pub(super) const TRB_ADDRESS_DEVICE: u32 = 11;
+pub(super) const TRB_CONFIGURE_ENDPOINT: u32 = 12;
+pub(super) const TRB_EVALUATE_CONTEXT: u32 = 13;
+pub(super) const TRB_RESET_ENDPOINT: u32 = 14;
+pub(super) const TRB_STOP_ENDPOINT: u32 = 15;
+pub(super) const TRB_SET_TR_DEQUEUE: u32 = 16;
+pub(super) const TRB_TRANSFER_EVENT: u32 = 32;
+pub(super) const TRB_COMMAND_COMPLETION: u32 = 33;
+
+// The bits of the control field.
+const TRB_CYCLE: u32 = 1 << 0;
+const TRB_TOGGLE_CYCLE: u32 = 1 << 1;
+pub(super) const TRB_ISP: u32 = 1 << 2;
+pub(super) const TRB_CHAIN: u32 = 1 << 4;
+pub(super) const TRB_IOC: u32 = 1 << 5;
+pub(super) const TRB_IDT: u32 = 1 << 6;
+pub(super) const TRB_DIR_IN: u32 = 1 << 16;
+
+// The completion codes.
+pub(super) const CC_SUCCESS: u8 = 1;
+const CC_BABBLE: u8 = 3;
+const CC_TRANSACTION: u8 = 4;
+const CC_STALL: u8 = 6;
+const CC_NO_SLOTS: u8 = 9;
+pub(super) const CC_SHORT_PACKET: u8 = 13;
+pub(super) const CC_STOPPED: u8 = 26;
+pub(super) const CC_STOPPED_LENGTH_INVALID: u8 = 27;
+
+/// A TRB.
+#[derive(Debug, Clone, Copy, Default)]
+pub(super) struct Trb {
+    pub parameter: u64,
+    pub status: u32,
+    pub control: u32,
+}
+
+impl Trb {
+    /// Creates a TRB of the type, with the other bits of the control field.
+    pub fn new(type_: u32, parameter: u64, status: u32, control: u32) -> Self {
+        Self {
+            parameter,
+            status,
+            control: type_ << 10 | control,
+        }
+    }
+
+    pub fn type_(&self) -> u32 {
+        (self.control >> 10) & 0x3F
+    }
+
+    pub fn completion_code(&self) -> u8 {
+        (self.status >> 24) as u8
+    }
+
+    /// Returns the number of bytes that are not transferred, for transfer events.
+    pub fn residual_len(&self) -> usize {
+        (self.status & 0xFF_FFFF) as usize
+    }
+
+    pub fn slot_id(&self) -> u8 {
+        (self.control >> 24) as u8
+    }
+
+    /// Returns the device context index of the endpoint, for transfer events.
+    pub fn endpoint_id(&self) -> u8 {
+        ((self.control >> 16) & 0x1F) as u8
+    }
+
+    /// Converts the completion code to the result.
+    pub fn result(&self) -> Result<(), UsbError> {
+        match self.completion_code() {
+            CC_SUCCESS | CC_SHORT_PACKET => Ok(()),
+            CC_STALL => Err(UsbError::Stall),
+            CC_BABBLE | CC_TRANSACTION => Err(UsbError::Transaction),
+            CC_NO_SLOTS => Err(UsbError::NoResource),
+            CC_STOPPED | CC_STOPPED_LENGTH_INVALID => Err(UsbError::Cancelled),
+            _ => Err(UsbError::Io),
+        }
+    }
+
+    fn read(dma: &DmaCoherent, index: usize) -> Self {
+        let offset = index * TRB_SIZE;
+        Self {
+            parameter: dma.read_val(offset).unwrap(),
+            status: dma.read_val(offset + 8).unwrap(),
+            control: dma.read_once(offset + 12).unwrap(),
+        }
+    }
+
+    fn write(&self, dma: &DmaCoherent, index: usize) {
+        let offset = index * TRB_SIZE;
+        dma.write_val(offset, &self.parameter).unwrap();
+        dma.write_val(offset + 8, &self.status).unwrap();
+        // The cycle bit hands the TRB over to the controller, so it is written last.
+        fence(Ordering::Release);
+        dma.write_once(offset + 12, &self.control).unwrap();
+    }
+}
+
+/// A command ring or a transfer ring.
+///
+/// The ring consists of one page, whose last TRB links to the first one.
+#[derive(Debug)]
+pub(super) struct Ring {
+    dma: DmaCoherent,
+    enqueue: usize,
+    cycle: bool,
+    /// The number of TRBs that are not completed by the controller.
+    nr_used: usize,
+}
+
+impl Ring {
+    pub fn new(is_64bit: bool) -> Result<Self, UsbError> {
+        let dma = alloc_dma(1, is_64bit)?;
+        let link = Trb::new(TRB_LINK, dma.daddr() as u64, 0, TRB_TOGGLE_CYCLE);
+        link.write(&dma, RING_LEN - 1);
+        Ok(Self {
+            dma,
+            enqueue: 0,
+            cycle: true,
+            nr_used: 0,
+        })
+    }
+
+    /// Returns the address that the dequeue pointer is set to, with the cycle state in bit 0.
+    pub fn dequeue_pointer(&self) -> u64 {
+        self.enqueue_daddr() as u64 | self.cycle as u64
+    }
+
+    fn enqueue_daddr(&self) -> Daddr {
+        self.dma.daddr() + self.enqueue * TRB_SIZE
+    }
+
+    /// Returns whether `nr_trbs` TRBs can be pushed.
+    pub fn has_room(&self, nr_trbs: usize) -> bool {
+        self.nr_used + nr_trbs < RING_LEN - 1
+    }
+
+    /// Pushes a TRB, returning its address.
+    pub fn push(&mut self, mut trb: Trb) -> Daddr {
+        debug_assert!(self.has_room(1));
+        let daddr = self.enqueue_daddr();
+        if self.cycle {
+            trb.control |= TRB_CYCLE;
+        }
+        trb.write(&self.dma, self.enqueue);
+        self.nr_used += 1;
+
+        self.enqueue += 1;
+        if self.enqueue == RING_LEN - 1 {
+            // Hand over the link TRB, whose chain bit must follow the TRB before it.
+            let mut link = Trb::new(
+                TRB_LINK,
+                self.dma.daddr() as u64,
+                0,
+                TRB_TOGGLE_CYCLE | (trb.control & TRB_CHAIN),
+            );
+            if self.cycle {
+                link.control |= TRB_CYCLE;
+            }
+            link.write(&self.dma, RING_LEN - 1);
+            self.enqueue = 0;
+            self.cycle = !self.cycle;
+        }
+        daddr
+    }
+
+    /// Marks the TRBs as completed by the controller.
+    pub fn release(&mut self, nr_trbs: usize) {
+        self.nr_used = self.nr_used.saturating_sub(nr_trbs);
+    }
+
+    /// Marks all the TRBs as completed, after the dequeue pointer is moved to the enqueue
+    /// pointer.
+    pub fn release_all(&mut self) {
+        self.nr_used = 0;
+    }
+}
+
+/// The event ring with a single segment.
+#[derive(Debug)]
+pub(super) struct EventRing {
+    /// The segment of the TRBs in the first page, and the segment table in the second page.
+    dma: DmaCoherent,
+    dequeue: usize,
+    cycle: bool,
+}
+
+impl EventRing {
+    pub fn new(is_64bit: bool) -> Result<Self, UsbError> {
+        let dma = alloc_dma(2, is_64bit)?;
+        // The only entry of the segment table.
+        dma.write_val(PAGE_SIZE, &(dma.daddr() as u64)).unwrap();
+        dma.write_val(PAGE_SIZE + 8, &(RING_LEN as u32)).unwrap();
+        Ok(Self {
+            dma,
+            dequeue: 0,
+            cycle: true,
+        })
+    }
+
+    /// Returns the address of the segment table.
+    pub fn segment_table_daddr(&self) -> Daddr {
+        self.dma.daddr() + PAGE_SIZE
+    }
+
+    /// Returns the address of the next event.
+    pub fn dequeue_daddr(&self) -> Daddr {
+        self.dma.daddr() + self.dequeue * TRB_SIZE
+    }
+
+    /// Pops an event that is written by the controller.
+    pub fn pop(&mut self) -> Option<Trb> {
+        let control: u32 = self.dma.read_once(self.dequeue * TRB_SIZE + 12).unwrap();
+        if (control & TRB_CYCLE != 0) != self.cycle {
+            return None;
+        }
+        fence(Ordering::Acquire);
+        let trb = Trb::read(&self.dma, self.dequeue);
+
+        self.dequeue += 1;
+        if self.dequeue == RING_LEN {
+            self.dequeue = 0;
+            self.cycle = !self.cycle;
+        }
+        Some(trb)
+    }
+}
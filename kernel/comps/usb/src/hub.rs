@@ -0,0 +1,226 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The root hubs and the hub class driver.
+//!
+//! The devices are enumerated once the hubs are found. The status change endpoints of the
+//! hubs are not polled, so the devices that are plugged later are not discovered.
+
+use alloc::sync::Arc;
+
+use log::{info, warn};
+
+use crate::{
+    class::UsbDriver,
+    descriptor::*,
+    device::UsbDevice,
+    host::{delay_ms, poll_until, DeviceRoute, HubInfo, UsbError, UsbHostController, UsbSpeed},
+};
+
+/// The class code of hubs.
+const CLASS_HUB: u8 = 0x09;
+/// The device protocol of the high-speed hubs with a transaction translator per port.
+const PROTOCOL_MULTI_TT: u8 = 0x02;
+
+// The descriptor types of hubs.
+const DESC_HUB: u8 = 0x29;
+const DESC_SS_HUB: u8 = 0x2A;
+
+/// The hub class request of setting the hub depth of SuperSpeed hubs.
+const SET_HUB_DEPTH: u8 = 12;
+
+// The port feature selectors.
+const PORT_RESET: u16 = 4;
+const PORT_POWER: u16 = 8;
+const C_PORT_CONNECTION: u16 = 16;
+const C_PORT_RESET: u16 = 20;
+
+// The bits of the port status.
+const STATUS_CONNECTION: u16 = 1 << 0;
+const STATUS_ENABLE: u16 = 1 << 1;
+const STATUS_LOW_SPEED: u16 = 1 << 9;
+const STATUS_HIGH_SPEED: u16 = 1 << 10;
+// The bits of the port status change.
+const CHANGE_RESET: u16 = 1 << 4;
+
+/// The maximum depth of the hubs, which is limited by the route string.
+const MAX_HUB_DEPTH: u8 = 5;
+
+/// The timeout for resetting a port (in microseconds).
+const PORT_RESET_TIMEOUT_US: u64 = 500_000;
+
+/// Enumerates the devices connected to the root hub of the host controller.
+pub(crate) fn enumerate_root_hub(host: &Arc<dyn UsbHostController>, bus: usize) {
+    for port in 1..=host.nr_ports() {
+        if !host.port_connected(port) {
+            continue;
+        }
+        let speed = match host.reset_port(port) {
+            Ok(speed) => speed,
+            Err(err) => {
+                warn!("[USB]: {}-{}: cannot reset the port: {:?}", bus, port, err);
+                continue;
+            }
+        };
+        let route = DeviceRoute {
+            root_port: port,
+            route_string: 0,
+            depth: 0,
+            speed,
+            tt: None,
+        };
+        crate::enumerate_device(host.clone(), bus, route);
+    }
+}
+
+/// The hub class driver.
+#[derive(Debug)]
+pub(crate) struct HubDriver;
+
+impl UsbDriver for HubDriver {
+    fn name(&self) -> &str {
+        "hub"
+    }
+
+    fn probe(&self, device: &Arc<UsbDevice>, interface: &InterfaceDescriptor) -> bool {
+        if interface.class != CLASS_HUB {
+            return false;
+        }
+        if device.route().depth >= MAX_HUB_DEPTH {
+            warn!("[USB]: {}: the hub is too deep", device.name());
+            return false;
+        }
+        if let Err(err) = probe_hub(device) {
+            warn!(
+                "[USB]: {}: hub initialization failed: {:?}",
+                device.name(),
+                err
+            );
+        }
+        true
+    }
+}
+
+fn probe_hub(device: &Arc<UsbDevice>) -> Result<(), UsbError> {
+    let is_superspeed = device.speed() >= UsbSpeed::Super;
+    let desc_type = if is_superspeed { DESC_SS_HUB } else { DESC_HUB };
+    let desc = device.control_in(
+        TYPE_CLASS | RECIP_DEVICE,
+        GET_DESCRIPTOR,
+        (desc_type as u16) << 8,
+        0,
+        16,
+    )?;
+    if desc.len() < 7 || desc[1] != desc_type {
+        return Err(UsbError::InvalidDescriptor);
+    }
+    let nr_ports = desc[2];
+    let characteristics = u16::from_le_bytes([desc[3], desc[4]]);
+    // The time (in 2 ms units) from powering on a port until the power is good.
+    let power_on_delay_ms = desc[5] as u64 * 2;
+
+    let info = HubInfo {
+        nr_ports,
+        multi_tt: device.descriptor().protocol == PROTOCOL_MULTI_TT,
+        tt_think_time: ((characteristics >> 5) & 0b11) as u8,
+    };
+    device.host().set_hub(device.slot(), info)?;
+    if is_superspeed {
+        device.control_out(
+            TYPE_CLASS | RECIP_DEVICE,
+            SET_HUB_DEPTH,
+            device.route().depth as u16,
+            0,
+            &[],
+        )?;
+    }
+    info!("[USB]: {}: hub with {} ports", device.name(), nr_ports);
+
+    for port in 1..=nr_ports {
+        set_port_feature(device, port, PORT_POWER)?;
+    }
+    delay_ms(power_on_delay_ms.max(100));
+
+    for port in 1..=nr_ports {
+        let (status, _) = port_status(device, port)?;
+        if status & STATUS_CONNECTION == 0 {
+            continue;
+        }
+        let speed = match reset_port(device, port, is_superspeed) {
+            Ok(speed) => speed,
+            Err(err) => {
+                warn!(
+                    "[USB]: {}.{}: cannot reset the port: {:?}",
+                    device.name(),
+                    port,
+                    err
+                );
+                continue;
+            }
+        };
+        let route = device.route().child(device.slot(), port, speed);
+        crate::enumerate_device(device.host().clone(), device.bus(), route);
+    }
+    Ok(())
+}
+
+fn reset_port(device: &UsbDevice, port: u8, is_superspeed: bool) -> Result<UsbSpeed, UsbError> {
+    set_port_feature(device, port, PORT_RESET)?;
+    let status = poll_until(PORT_RESET_TIMEOUT_US, || {
+        delay_ms(10);
+        match port_status(device, port) {
+            Ok((status, change)) if change & CHANGE_RESET != 0 => Some(Ok(status)),
+            Ok(_) => None,
+            Err(err) => Some(Err(err)),
+        }
+    })??;
+    clear_port_feature(device, port, C_PORT_RESET)?;
+    clear_port_feature(device, port, C_PORT_CONNECTION)?;
+    if status & STATUS_ENABLE == 0 {
+        return Err(UsbError::Io);
+    }
+    // The reset recovery time.
+    delay_ms(10);
+
+    let speed = if is_superspeed {
+        UsbSpeed::Super
+    } else if status & STATUS_LOW_SPEED != 0 {
+        UsbSpeed::Low
+    } else if status & STATUS_HIGH_SPEED != 0 {
+        UsbSpeed::High
+    } else {
+        UsbSpeed::Full
+    };
+    Ok(speed)
+}
+
+/// Returns the status and the status change of the port.
+fn port_status(device: &UsbDevice, port: u8) -> Result<(u16, u16), UsbError> {
+    let bytes = device.control_in(TYPE_CLASS | RECIP_OTHER, GET_STATUS, 0, port as u16, 4)?;
+    if bytes.len() < 4 {
+        return Err(UsbError::Transaction);
+    }
+    Ok((
+        u16::from_le_bytes([bytes[0], bytes[1]]),
+        u16::from_le_bytes([bytes[2], bytes[3]]),
+    ))
+}
+
+fn set_port_feature(device: &UsbDevice, port: u8, feature: u16) -> Result<(), UsbError> {
+    device.control_out(
+        TYPE_CLASS | RECIP_OTHER,
+        SET_FEATURE,
+        feature,
+        port as u16,
+        &[],
+    )
+}
+
+fn clear_port_feature(device: &UsbDevice, port: u8, feature: u16) -> Result<(), UsbError> {
+    device.control_out(
+        TYPE_CLASS | RECIP_OTHER,
+        CLEAR_FEATURE,
+        feature,
+        port as u16,
+        &[],
+    )
+}
@@ -0,0 +1,116 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The USB subsystem of Asterinas.
+//!
+//! This crate drives USB devices attached to USB host controllers. It is organized in
+//! four layers:
+//!
+//! - The host layer (`host`) abstracts the controllers. Each controller driver implements
+//!   the [`UsbHostController`] trait, which addresses the devices and runs the transfers
+//!   described by [`Urb`]s. Currently, the xHCI controllers are supported, either on the
+//!   PCI bus or in the device tree (including the Synopsys DesignWare USB3 controllers).
+//! - The device layer (`device`) enumerates the devices and issues the control, bulk, and
+//!   interrupt transfers of [`UsbDevice`]s.
+//! - The hub layer (`hub`) resets the ports of the root hubs and the external hubs, and
+//!   enumerates the devices connected to them.
+//! - The class layer (`class`) binds the [`UsbDriver`]s to the interfaces of the devices.
+//!   Mass storage devices (using the Bulk-Only Transport) are registered as block devices
+//!   named `sdX` in `aster-block`, and boot protocol keyboards are registered as input
+//!   devices named `usb-kbdN` in `aster-input`.
+//!
+//! The devices are only enumerated once during initialization, so hot-plugging is not
+//! supported. There is no interrupt controller support on RISC-V yet, so the controllers
+//! are polled.
+#![no_std]
+#![deny(unsafe_code)]
+
+extern crate alloc;
+
+mod class;
+pub mod descriptor;
+mod device;
+mod host;
+mod hub;
+mod pci;
+#[cfg(target_arch = "riscv64")]
+mod probe;
+
+use alloc::{sync::Arc, vec::Vec};
+
+use component::{init_component, ComponentInitError};
+use log::{info, warn};
+use ostd::sync::{LocalIrqDisabled, SpinLock};
+
+pub use self::{
+    class::{hid::UsbKeyboard, register_driver, storage::UsbStorageBlockDevice, UsbDriver},
+    device::UsbDevice,
+    host::{DeviceRoute, HubInfo, SlotId, Urb, UsbError, UsbHostController, UsbSpeed},
+};
+
+#[init_component]
+fn usb_component_init() -> Result<(), ComponentInitError> {
+    register_driver(Arc::new(hub::HubDriver));
+    register_driver(Arc::new(class::storage::StorageDriver));
+    register_driver(Arc::new(class::hid::HidDriver));
+
+    for host in pci::probe_hosts() {
+        register_host(host);
+    }
+    #[cfg(target_arch = "riscv64")]
+    for host in probe::probe_hosts() {
+        register_host(host);
+    }
+    Ok(())
+}
+
+/// Registers a host controller and enumerates the devices connected to it.
+///
+/// The buses are numbered from 1 in the order of registration.
+pub fn register_host(host: Arc<dyn UsbHostController>) {
+    let bus = {
+        let mut hosts = HOSTS.lock();
+        hosts.push(host.clone());
+        hosts.len()
+    };
+    info!(
+        "[USB]: bus {}: host controller {} with {} ports",
+        bus,
+        host.name(),
+        host.nr_ports()
+    );
+    hub::enumerate_root_hub(&host, bus);
+}
+
+/// Returns all the enumerated devices.
+pub fn all_devices() -> Vec<Arc<UsbDevice>> {
+    DEVICES.lock().clone()
+}
+
+/// Enumerates the device at `route`, and binds the class drivers to it.
+pub(crate) fn enumerate_device(host: Arc<dyn UsbHostController>, bus: usize, route: DeviceRoute) {
+    let speed = route.speed;
+    let device = match UsbDevice::enumerate(host, bus, route) {
+        Ok(device) => device,
+        Err(err) => {
+            warn!("[USB]: bus {}: cannot enumerate the device: {:?}", bus, err);
+            return;
+        }
+    };
+
+    let descriptor = device.descriptor();
+    info!(
+        "[USB]: {}: new {:?}-speed device {:04x}:{:04x} {}",
+        device.name(),
+        speed,
+        descriptor.vendor_id,
+        descriptor.product_id,
+        device.product().unwrap_or("")
+    );
+    DEVICES.lock().push(device.clone());
+    class::bind_drivers(&device);
+}
+
+static HOSTS: SpinLock<Vec<Arc<dyn UsbHostController>>, LocalIrqDisabled> =
+    SpinLock::new(Vec::new());
+
+static DEVICES: SpinLock<Vec<Arc<UsbDevice>>, LocalIrqDisabled> = SpinLock::new(Vec::new());
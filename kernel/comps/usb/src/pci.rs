@@ -0,0 +1,98 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The PCI driver of xHCI controllers.
+
+use alloc::{format, sync::Arc, vec::Vec};
+
+use log::{info, warn};
+use ostd::{
+    bus::{
+        pci::{
+            bus::{PciDevice, PciDriver},
+            cfg_space::{Bar, Command},
+            common_device::PciCommonDevice,
+            PciDeviceId, PCI_BUS,
+        },
+        BusProbeError,
+    },
+    io::MmioRegion,
+    sync::{LocalIrqDisabled, SpinLock},
+};
+
+use crate::host::{xhci::Xhci, UsbHostController};
+
+// The class code of xHCI controllers.
+const CLASS_SERIAL_BUS: u8 = 0x0C;
+const SUBCLASS_USB: u8 = 0x03;
+const PROG_IF_XHCI: u8 = 0x30;
+
+/// The controllers that are probed but not registered yet.
+static PROBED_HOSTS: SpinLock<Vec<Arc<dyn UsbHostController>>, LocalIrqDisabled> =
+    SpinLock::new(Vec::new());
+
+/// Probes the xHCI controllers on the PCI bus.
+///
+/// The devices are not enumerated while probing, since the PCI bus is locked.
+pub(crate) fn probe_hosts() -> Vec<Arc<dyn UsbHostController>> {
+    PCI_BUS.lock().register_driver(Arc::new(XhciPciDriver));
+    core::mem::take(&mut *PROBED_HOSTS.lock())
+}
+
+#[derive(Debug)]
+struct XhciPciDriver;
+
+impl PciDriver for XhciPciDriver {
+    fn probe(
+        &self,
+        device: PciCommonDevice,
+    ) -> Result<Arc<dyn PciDevice>, (BusProbeError, PciCommonDevice)> {
+        let device_id = *device.device_id();
+        if device_id.class != CLASS_SERIAL_BUS
+            || device_id.subclass != SUBCLASS_USB
+            || device_id.prog_if != PROG_IF_XHCI
+        {
+            return Err((BusProbeError::DeviceNotMatch, device));
+        }
+        let Some(Bar::Memory(bar)) = device.bar_manager().bar(0).clone() else {
+            return Err((BusProbeError::ConfigurationSpaceError, device));
+        };
+
+        let location = *device.location();
+        let name = format!(
+            "{:02x}:{:02x}.{}",
+            location.bus, location.device, location.function
+        );
+        let io_mem = match MmioRegion::claim_pci_bar(&bar, "xhci") {
+            Ok(io_mem) => io_mem,
+            Err(err) => {
+                warn!("[xHCI]: {}: cannot claim the registers: {:?}", name, err);
+                return Err((BusProbeError::ConfigurationSpaceError, device));
+            }
+        };
+        device.set_command(device.command() | Command::MEMORY_SPACE | Command::BUS_MASTER);
+
+        match Xhci::new(name.clone(), io_mem) {
+            Ok(xhci) => {
+                info!("[xHCI]: found PCI host controller {}", name);
+                PROBED_HOSTS.lock().push(Arc::new(xhci));
+            }
+            Err(err) => warn!("[xHCI]: {}: initialization failed: {:?}", name, err),
+        }
+        Ok(Arc::new(XhciPciDevice {
+            device_id,
+            _common_device: device,
+        }))
+    }
+}
+
+#[derive(Debug)]
+struct XhciPciDevice {
+    device_id: PciDeviceId,
+    _common_device: PciCommonDevice,
+}
+
+impl PciDevice for XhciPciDevice {
+    fn device_id(&self) -> PciDeviceId {
+        self.device_id
+    }
+}
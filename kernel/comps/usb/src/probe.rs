@@ -0,0 +1,82 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Discovery of xHCI host controllers in the device tree.
+
+use alloc::{string::ToString, sync::Arc, vec::Vec};
+
+use log::{info, warn};
+use ostd::{arch::boot::DEVICE_TREE, io::MmioRegion};
+
+use crate::host::{xhci::Xhci, UsbHostController};
+
+/// The compatible strings of the generic xHCI controllers.
+const XHCI_COMPATIBLES: &[&str] = &["generic-xhci", "xhci-platform"];
+
+/// The compatible strings of the Synopsys DesignWare USB3 controllers, which contain an
+/// xHCI controller in the host mode.
+const DWC3_COMPATIBLES: &[&str] = &["snps,dwc3", "starfive,jh7110-dwc3"];
+
+/// The global control register of DWC3 controllers.
+const DWC3_GCTL: usize = 0xC110;
+/// The port capability direction field of `DWC3_GCTL`.
+const DWC3_GCTL_PRTCAPDIR_MASK: u32 = 0b11 << 12;
+const DWC3_GCTL_PRTCAPDIR_HOST: u32 = 0b01 << 12;
+
+/// Probes the xHCI host controllers described in the device tree.
+pub(crate) fn probe_hosts() -> Vec<Arc<dyn UsbHostController>> {
+    let mut hosts: Vec<Arc<dyn UsbHostController>> = Vec::new();
+
+    for node in DEVICE_TREE.get().unwrap().all_nodes() {
+        let Some(compatible) = node.compatible() else {
+            continue;
+        };
+        let is_xhci = compatible.all().any(|c| XHCI_COMPATIBLES.contains(&c));
+        let is_dwc3 = compatible.all().any(|c| DWC3_COMPATIBLES.contains(&c));
+        if !is_xhci && !is_dwc3 {
+            continue;
+        }
+        if node
+            .property("status")
+            .and_then(|prop| prop.as_str())
+            .is_some_and(|status| status != "okay" && status != "ok")
+        {
+            continue;
+        }
+        // The DWC3 controllers in the peripheral mode are not host controllers.
+        if is_dwc3
+            && node
+                .property("dr_mode")
+                .and_then(|prop| prop.as_str())
+                .is_some_and(|mode| mode == "peripheral")
+        {
+            continue;
+        }
+
+        let io_mem = match MmioRegion::claim_fdt_reg(&node) {
+            Ok(io_mem) => io_mem,
+            Err(err) => {
+                warn!(
+                    "[xHCI]: {}: cannot claim the registers: {:?}",
+                    node.name, err
+                );
+                continue;
+            }
+        };
+
+        if is_dwc3 {
+            let gctl: u32 = io_mem.read_once(DWC3_GCTL).unwrap();
+            let gctl = (gctl & !DWC3_GCTL_PRTCAPDIR_MASK) | DWC3_GCTL_PRTCAPDIR_HOST;
+            io_mem.write_once(DWC3_GCTL, &gctl).unwrap();
+        }
+
+        match Xhci::new(node.name.to_string(), io_mem) {
+            Ok(xhci) => {
+                info!("[xHCI]: found host controller {}", node.name);
+                hosts.push(Arc::new(xhci));
+            }
+            Err(err) => warn!("[xHCI]: {}: initialization failed: {:?}", node.name, err),
+        }
+    }
+
+    hosts
+}
@@ -100,6 +100,14 @@ impl aster_block::BlockDevice for BlockDevice {
             nr_sectors: self.device.config_manager.capacity_sectors(),
         }
     }
+
+    fn plug(&self) {
+        self.queue.plug();
+    }
+
+    fn unplug(&self) {
+        self.queue.unplug();
+    }
 }
 
 #[derive(Debug)]
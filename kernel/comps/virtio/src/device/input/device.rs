@@ -1,16 +1,15 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use alloc::{
-    boxed::Box,
-    string::{String, ToString},
-    sync::Arc,
-    vec::Vec,
+use alloc::{boxed::Box, format, string::String, sync::Arc, vec::Vec};
+use core::{
+    fmt::Debug,
+    iter, mem,
+    sync::atomic::{AtomicUsize, Ordering},
 };
-use core::{fmt::Debug, iter, mem};
 
 use aster_input::{
-    key::{Key, KeyStatus},
-    InputEvent,
+    event::{self, ABS_CNT, BUS_VIRTUAL, INPUT_PROP_CNT, KEY_CNT, REL_CNT},
+    InputCapability, InputEvent, InputId,
 };
 use aster_util::{field_ptr, safe_ptr::SafePtr};
 use bitflags::bitflags;
@@ -21,9 +20,13 @@ use ostd::{
     offset_of,
     sync::{LocalIrqDisabled, RwLock, SpinLock},
     trap::TrapFrame,
+    Pod,
 };
 
-use super::{InputConfigSelect, VirtioInputConfig, VirtioInputEvent, QUEUE_EVENT, QUEUE_STATUS};
+use super::{
+    AbsInfo, DevIds, InputConfigSelect, VirtioInputConfig, VirtioInputEvent, QUEUE_EVENT,
+    QUEUE_STATUS,
+};
 use crate::{
     device::VirtioDeviceError, dma_buf::DmaBuf, queue::VirtQueue, transport::VirtioTransport,
 };
@@ -75,6 +78,9 @@ pub struct InputDevice {
     event_queue: SpinLock<VirtQueue>,
     status_queue: VirtQueue,
     event_table: EventTable,
+    name: String,
+    id: InputId,
+    capability: InputCapability,
     #[expect(clippy::type_complexity)]
     callbacks: RwLock<Vec<Arc<dyn Fn(InputEvent) + Send + Sync + 'static>>, LocalIrqDisabled>,
     transport: SpinLock<Box<dyn VirtioTransport>>,
@@ -103,17 +109,23 @@ impl InputDevice {
             }
         }
 
+        let config = VirtioInputConfig::new(transport.as_mut());
+        let name = query_config_string(&config, InputConfigSelect::IdName);
+        let id = query_config_id(&config);
+        let capability = query_config_capability(&config);
         let device = Arc::new(Self {
-            config: VirtioInputConfig::new(transport.as_mut()),
+            config,
             event_queue: SpinLock::new(event_queue),
             status_queue,
             event_table,
+            name,
+            id,
+            capability,
             transport: SpinLock::new(transport),
             callbacks: RwLock::new(Vec::new()),
         });
 
-        let name = device.query_config_id_name();
-        info!("Virtio input device name:{}", name);
+        info!("Virtio input device name:{}", device.name);
 
         let input_prop = device.query_config_prop_bits();
         if let Some(prop) = input_prop {
@@ -141,51 +153,37 @@ impl InputDevice {
         transport.finish_init();
         drop(transport);
 
-        aster_input::register_device(super::DEVICE_NAME.to_string(), device);
+        // There may be several input devices (e.g., a keyboard and a mouse), so they are
+        // numbered to have unique names.
+        let index = NEXT_INDEX.fetch_add(1, Ordering::Relaxed);
+        aster_input::register_device(format!("{}{}", super::DEVICE_NAME, index), device);
 
         Ok(())
     }
 
     /// Pop the pending event.
-    fn pop_pending_events(&self, handle_event: &impl Fn(&EventBuf) -> bool) {
+    fn pop_pending_events(&self, handle_event: &impl Fn(&EventBuf)) {
         let mut event_queue = self.event_queue.disable_irq().lock();
 
         // one interrupt may contain several input events, so it should loop
         while let Ok((token, _)) = event_queue.pop_used() {
             debug_assert!(token < QUEUE_SIZE);
             let ptr = self.event_table.get(token as usize);
-            let res = handle_event(&ptr);
+            handle_event(&ptr);
             let new_token = event_queue.add_dma_buf(&[], &[&ptr]).unwrap();
             // This only works because nothing happen between `pop_used` and `add` that affects
             // the list of free descriptors in the queue, so `add` reuses the descriptor which
             // was just freed by `pop_used`.
             assert_eq!(new_token, token);
-
-            if !res {
-                break;
-            }
         }
     }
 
     pub fn query_config_id_name(&self) -> String {
-        let size = self.select_config(InputConfigSelect::IdName, 0);
-
-        let out = {
-            // TODO: Add a general API to read this byte-by-byte.
-            let mut out = Vec::with_capacity(size);
-            let mut data_ptr = field_ptr!(&self.config, VirtioInputConfig, data).cast::<u8>();
-            for _ in 0..size {
-                out.push(data_ptr.read_once().unwrap());
-                data_ptr.byte_add(1);
-            }
-            out
-        };
-
-        String::from_utf8(out).unwrap()
+        query_config_string(&self.config, InputConfigSelect::IdName)
     }
 
     pub fn query_config_prop_bits(&self) -> Option<InputProp> {
-        let size = self.select_config(InputConfigSelect::PropBits, 0);
+        let size = select_config(&self.config, InputConfigSelect::PropBits, 0);
         if size == 0 {
             return None;
         }
@@ -195,48 +193,23 @@ impl InputDevice {
         InputProp::from_bits(data_ptr.cast::<u8>().read_once().unwrap())
     }
 
-    /// Query a specific piece of information by `select` and `subsel`, return the result size.
-    fn select_config(&self, select: InputConfigSelect, subsel: u8) -> usize {
-        field_ptr!(&self.config, VirtioInputConfig, select)
-            .write_once(&(select as u8))
-            .unwrap();
-        field_ptr!(&self.config, VirtioInputConfig, subsel)
-            .write_once(&subsel)
-            .unwrap();
-        field_ptr!(&self.config, VirtioInputConfig, size)
-            .read_once()
-            .unwrap() as usize
-    }
-
     fn handle_irq(&self) {
         let callbacks = self.callbacks.read();
-        // Returns true if there may be more events to handle
-        let handle_event = |event: &EventBuf| -> bool {
+        let handle_event = |event: &EventBuf| {
             event.sync().unwrap();
             let event: VirtioInputEvent = event.read().unwrap();
 
-            match event.event_type {
-                0 => return false,
-                // Keyboard
-                1 => {}
-                // TODO: Support mouse device.
-                _ => return true,
-            }
-
-            let status = match event.value {
-                1 => KeyStatus::Pressed,
-                0 => KeyStatus::Released,
-                _ => return false,
+            // The events are those of evdev, so they only need to be normalized.
+            let Some(event) =
+                InputEvent::from_raw(event.event_type, event.code, event.value as i32)
+            else {
+                return;
             };
-
-            let event = InputEvent::KeyBoard(Key::try_from(event.code).unwrap(), status);
-            info!("Input Event:{:?}", event);
+            debug!("Input Event:{:?}", event);
 
             for callback in callbacks.iter() {
                 callback(event);
             }
-
-            true
         };
 
         self.pop_pending_events(&handle_event);
@@ -293,10 +266,132 @@ impl<T, M: HasDaddr> DmaBuf for SafePtr<T, M> {
     }
 }
 
+/// The index of the next input device.
+static NEXT_INDEX: AtomicUsize = AtomicUsize::new(0);
+
+/// Queries a specific piece of information by `select` and `subsel`, returning the result size.
+fn select_config(
+    config: &SafePtr<VirtioInputConfig, IoMem>,
+    select: InputConfigSelect,
+    subsel: u8,
+) -> usize {
+    field_ptr!(config, VirtioInputConfig, select)
+        .write_once(&(select as u8))
+        .unwrap();
+    field_ptr!(config, VirtioInputConfig, subsel)
+        .write_once(&subsel)
+        .unwrap();
+    field_ptr!(config, VirtioInputConfig, size)
+        .read_once()
+        .unwrap() as usize
+}
+
+/// Queries a specific piece of information by `select` and `subsel`, returning the data.
+fn query_config_data(
+    config: &SafePtr<VirtioInputConfig, IoMem>,
+    select: InputConfigSelect,
+    subsel: u8,
+) -> Vec<u8> {
+    let size = select_config(config, select, subsel);
+
+    // TODO: Add a general API to read this byte-by-byte.
+    let mut out = Vec::with_capacity(size);
+    let mut data_ptr = field_ptr!(config, VirtioInputConfig, data).cast::<u8>();
+    for _ in 0..size {
+        out.push(data_ptr.read_once().unwrap());
+        data_ptr.byte_add(1);
+    }
+    out
+}
+
+fn query_config_string(
+    config: &SafePtr<VirtioInputConfig, IoMem>,
+    select: InputConfigSelect,
+) -> String {
+    let data = query_config_data(config, select, 0);
+    String::from_utf8_lossy(&data).into_owned()
+}
+
+fn query_config_id(config: &SafePtr<VirtioInputConfig, IoMem>) -> InputId {
+    let data = query_config_data(config, InputConfigSelect::IdDevids, 0);
+    if data.len() < mem::size_of::<DevIds>() {
+        return InputId {
+            bus_type: BUS_VIRTUAL,
+            ..InputId::default()
+        };
+    }
+    let ids = DevIds::from_bytes(&data);
+    InputId {
+        bus_type: ids.bustype,
+        vendor: ids.vendor,
+        product: ids.product,
+        version: ids.version,
+    }
+}
+
+/// Queries the events that the device may report.
+fn query_config_capability(config: &SafePtr<VirtioInputConfig, IoMem>) -> InputCapability {
+    let mut capability = InputCapability::new();
+
+    let props = query_config_data(config, InputConfigSelect::PropBits, 0);
+    for_each_bit(&props, INPUT_PROP_CNT, |prop| capability.set_property(prop));
+
+    let keys = query_config_data(config, InputConfigSelect::EvBits, KEY);
+    for_each_bit(&keys, KEY_CNT, |code| capability.set_key(code));
+
+    let rels = query_config_data(config, InputConfigSelect::EvBits, REL);
+    for_each_bit(&rels, REL_CNT, |code| capability.set_relative(code));
+
+    let abs = query_config_data(config, InputConfigSelect::EvBits, ABS);
+    for_each_bit(&abs, ABS_CNT, |code| {
+        let data = query_config_data(config, InputConfigSelect::AbsInfo, code as u8);
+        if data.len() < mem::size_of::<AbsInfo>() {
+            return;
+        }
+        let info = AbsInfo::from_bytes(&data);
+        capability.set_absolute(
+            code,
+            event::AbsInfo {
+                minimum: info.min as i32,
+                maximum: info.max as i32,
+                fuzz: info.fuzz as i32,
+                flat: info.flat as i32,
+                resolution: info.res as i32,
+            },
+        );
+    });
+
+    capability
+}
+
+/// Calls `f` with the index of each set bit in the bitmap, up to `nr_bits`.
+fn for_each_bit(bitmap: &[u8], nr_bits: usize, mut f: impl FnMut(u16)) {
+    for (byte_index, byte) in bitmap.iter().enumerate() {
+        for bit in 0..8 {
+            let index = byte_index * 8 + bit;
+            if index < nr_bits && byte & (1 << bit) != 0 {
+                f(index as u16);
+            }
+        }
+    }
+}
+
 impl aster_input::InputDevice for InputDevice {
     fn register_callbacks(&self, function: &'static (dyn Fn(InputEvent) + Send + Sync)) {
         self.callbacks.write().push(Arc::new(function))
     }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn id(&self) -> InputId {
+        self.id
+    }
+
+    fn capability(&self) -> InputCapability {
+        self.capability.clone()
+    }
 }
 
 impl Debug for InputDevice {
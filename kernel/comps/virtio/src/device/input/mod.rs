@@ -84,7 +84,6 @@ impl VirtioInputConfig {
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, Pod)]
-#[expect(dead_code)]
 struct AbsInfo {
     min: u32,
     max: u32,
@@ -95,7 +94,6 @@ struct AbsInfo {
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, Pod)]
-#[expect(dead_code)]
 struct DevIds {
     bustype: u16,
     vendor: u16,
@@ -0,0 +1,116 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The driver of the Synopsys DesignWare watchdog.
+//!
+//! The watchdog counts down from one of 16 fixed timeout periods (TOPs), where the `i`-th
+//! period is `2^(16 + i)` cycles of the watchdog clock. The watchdog is configured to reset
+//! the system at the first timeout, since its interrupt is not used. Once started, the
+//! watchdog cannot be stopped except by a reset.
+//!
+//! Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/drivers/watchdog/dw_wdt.c>
+
+use alloc::string::String;
+use core::ops::RangeInclusive;
+
+use ostd::{io::MmioRegion, mm::VmIoOnce};
+
+use crate::{WatchdogDriver, WatchdogError};
+
+// The registers.
+const WDOG_CONTROL_REG: usize = 0x00;
+const WDOG_TIMEOUT_RANGE_REG: usize = 0x04;
+const WDOG_CURRENT_COUNT_REG: usize = 0x08;
+const WDOG_COUNTER_RESTART_REG: usize = 0x0c;
+
+// The bits of the control register.
+const WDOG_CONTROL_REG_WDT_EN: u32 = 1 << 0;
+const WDOG_CONTROL_REG_RESP_MODE: u32 = 1 << 1;
+
+/// The shift of the initial TOP in the timeout range register.
+const WDOG_TIMEOUT_RANGE_TOPINIT_SHIFT: u32 = 4;
+
+/// The value written to the counter restart register to restart the countdown.
+const WDOG_COUNTER_RESTART_KICK_VALUE: u32 = 0x76;
+
+/// The number of the fixed timeout periods.
+const DW_WDT_NUM_TOPS: u32 = 16;
+
+/// The Synopsys DesignWare watchdog.
+#[derive(Debug)]
+pub(crate) struct DwWdt {
+    name: String,
+    io_mem: MmioRegion,
+    /// The frequency of the watchdog clock in Hz
+    clock: u32,
+}
+
+impl DwWdt {
+    pub(crate) fn new(name: String, io_mem: MmioRegion, clock: u32) -> Self {
+        Self {
+            name,
+            io_mem,
+            clock,
+        }
+    }
+
+    /// Returns the timeout (in seconds) of the `top`-th period, rounded down.
+    fn top_in_seconds(&self, top: u32) -> u32 {
+        ((1u64 << (16 + top)) / self.clock as u64) as u32
+    }
+
+    fn read_reg(&self, offset: usize) -> u32 {
+        self.io_mem.read_once(offset).unwrap()
+    }
+
+    fn write_reg(&self, offset: usize, value: u32) {
+        self.io_mem.write_once(offset, &value).unwrap();
+    }
+}
+
+impl WatchdogDriver for DwWdt {
+    fn identity(&self) -> &str {
+        &self.name
+    }
+
+    fn timeout_range(&self) -> RangeInclusive<u32> {
+        1..=self.top_in_seconds(DW_WDT_NUM_TOPS - 1).max(1)
+    }
+
+    fn set_timeout(&self, timeout: u32) -> u32 {
+        // Use the shortest period that is not shorter than the timeout, or the longest one.
+        let top = (0..DW_WDT_NUM_TOPS)
+            .find(|top| self.top_in_seconds(*top) >= timeout)
+            .unwrap_or(DW_WDT_NUM_TOPS - 1);
+        self.write_reg(
+            WDOG_TIMEOUT_RANGE_REG,
+            top | (top << WDOG_TIMEOUT_RANGE_TOPINIT_SHIFT),
+        );
+        self.ping();
+        self.top_in_seconds(top).max(1)
+    }
+
+    fn start(&self) {
+        self.ping();
+        let control = self.read_reg(WDOG_CONTROL_REG);
+        self.write_reg(
+            WDOG_CONTROL_REG,
+            (control & !WDOG_CONTROL_REG_RESP_MODE) | WDOG_CONTROL_REG_WDT_EN,
+        );
+    }
+
+    fn stop(&self) -> Result<(), WatchdogError> {
+        Err(WatchdogError::NotSupported)
+    }
+
+    fn ping(&self) {
+        self.write_reg(WDOG_COUNTER_RESTART_REG, WDOG_COUNTER_RESTART_KICK_VALUE);
+    }
+
+    fn time_left(&self) -> Option<u32> {
+        Some(self.read_reg(WDOG_CURRENT_COUNT_REG) / self.clock)
+    }
+
+    fn is_running(&self) -> bool {
+        self.read_reg(WDOG_CONTROL_REG) & WDOG_CONTROL_REG_WDT_EN != 0
+    }
+}
@@ -0,0 +1,196 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The driver of the Intel 6300ESB watchdog.
+//!
+//! The watchdog has two stages, each of which counts down the same preload value at 1 kHz.
+//! The first stage is intended to raise an interrupt, which is not used, and the second
+//! stage resets the system. The registers in the memory BAR are locked after each write,
+//! so each write must be preceded by the unlock sequence.
+//!
+//! Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/drivers/watchdog/i6300esb.c>
+
+use alloc::sync::Arc;
+use core::ops::RangeInclusive;
+
+use log::{info, warn};
+use ostd::{
+    bus::{
+        pci::{
+            bus::{PciDevice, PciDriver},
+            cfg_space::{Bar, MemoryBar},
+            common_device::PciCommonDevice,
+            PciDeviceId, PCI_BUS,
+        },
+        BusProbeError,
+    },
+    mm::VmIoOnce,
+};
+
+use crate::{register_watchdog, WatchdogDriver, WatchdogError, WatchdogOptions};
+
+const ESB_VENDOR_ID: u16 = 0x8086;
+const ESB_DEVICE_ID: u16 = 0x25ab;
+
+// The registers in the configuration space.
+const ESB_CONFIG_REG: u16 = 0x60;
+const ESB_LOCK_REG: u16 = 0x68;
+
+// The registers in the memory BAR.
+const ESB_TIMER1_REG: usize = 0x00;
+const ESB_TIMER2_REG: usize = 0x04;
+const ESB_RELOAD_REG: usize = 0x0c;
+
+// The bits of the lock register.
+const ESB_WDT_LOCK: u8 = 1 << 0;
+const ESB_WDT_ENABLE: u8 = 1 << 1;
+
+// The bits of the configuration register.
+const ESB_WDT_INTTYPE: u16 = 0x03;
+
+// The bits of the reload register.
+const ESB_WDT_RELOAD: u16 = 1 << 8;
+const ESB_WDT_TIMEOUT: u16 = 1 << 9;
+
+// The unlock sequence.
+const ESB_UNLOCK1: u16 = 0x80;
+const ESB_UNLOCK2: u16 = 0x86;
+
+/// The maximum timeout in seconds, which keeps the preload value in 20 bits.
+const ESB_HEARTBEAT_MAX: u32 = 2046;
+
+pub(super) fn init() {
+    PCI_BUS.lock().register_driver(Arc::new(I6300EsbDriver));
+}
+
+#[derive(Debug)]
+struct I6300EsbDriver;
+
+impl PciDriver for I6300EsbDriver {
+    fn probe(
+        &self,
+        device: PciCommonDevice,
+    ) -> Result<Arc<dyn PciDevice>, (BusProbeError, PciCommonDevice)> {
+        let device_id = *device.device_id();
+        if device_id.vendor_id != ESB_VENDOR_ID || device_id.device_id != ESB_DEVICE_ID {
+            return Err((BusProbeError::DeviceNotMatch, device));
+        }
+        let Some(Bar::Memory(bar)) = device.bar_manager().bar(0).clone() else {
+            return Err((BusProbeError::ConfigurationSpaceError, device));
+        };
+
+        let esb = Arc::new(I6300Esb::new(device, bar));
+        register_watchdog(esb.clone());
+        info!("[Watchdog]: found i6300ESB watchdog");
+        Ok(Arc::new(I6300EsbDevice { device_id }))
+    }
+}
+
+#[derive(Debug)]
+struct I6300EsbDevice {
+    device_id: PciDeviceId,
+}
+
+impl PciDevice for I6300EsbDevice {
+    fn device_id(&self) -> PciDeviceId {
+        self.device_id
+    }
+}
+
+/// The Intel 6300ESB watchdog.
+#[derive(Debug)]
+struct I6300Esb {
+    device: PciCommonDevice,
+    bar: Arc<MemoryBar>,
+    boot_status: WatchdogOptions,
+}
+
+impl I6300Esb {
+    fn new(device: PciCommonDevice, bar: Arc<MemoryBar>) -> Self {
+        // Use the 1 kHz clock, and do not raise interrupts in the first stage.
+        device.write_config_u16(ESB_CONFIG_REG, ESB_WDT_INTTYPE);
+        if device.read_config_u8(ESB_LOCK_REG) & ESB_WDT_LOCK != 0 {
+            warn!("[Watchdog]: the i6300ESB watchdog is locked and cannot be stopped");
+        }
+        // Use the watchdog mode, and stop the countdown for now.
+        device.write_config_u8(ESB_LOCK_REG, 0);
+
+        let mut esb = Self {
+            device,
+            bar,
+            boot_status: WatchdogOptions::empty(),
+        };
+        esb.unlock_registers();
+        if esb.read_reload_reg() & ESB_WDT_TIMEOUT != 0 {
+            esb.boot_status = WatchdogOptions::CARDRESET;
+        }
+        // Clear the timeout flag and reload the timers.
+        esb.unlock_registers();
+        esb.write_reload_reg(ESB_WDT_TIMEOUT | ESB_WDT_RELOAD);
+        esb
+    }
+
+    fn unlock_registers(&self) {
+        self.write_reload_reg(ESB_UNLOCK1);
+        self.write_reload_reg(ESB_UNLOCK2);
+    }
+
+    fn read_reload_reg(&self) -> u16 {
+        self.bar.io_mem().read_once(ESB_RELOAD_REG).unwrap()
+    }
+
+    fn write_reload_reg(&self, value: u16) {
+        self.bar
+            .io_mem()
+            .write_once(ESB_RELOAD_REG, &value)
+            .unwrap();
+    }
+
+    fn write_timer_reg(&self, offset: usize, value: u32) {
+        self.unlock_registers();
+        self.bar.io_mem().write_once(offset, &value).unwrap();
+    }
+}
+
+impl WatchdogDriver for I6300Esb {
+    fn identity(&self) -> &str {
+        "i6300ESB timer"
+    }
+
+    fn timeout_range(&self) -> RangeInclusive<u32> {
+        1..=ESB_HEARTBEAT_MAX
+    }
+
+    fn set_timeout(&self, timeout: u32) -> u32 {
+        // Each of the two stages counts down `timeout << 9` at 1 kHz, so the system is reset
+        // after about `timeout` seconds.
+        let value = timeout << 9;
+        self.write_timer_reg(ESB_TIMER1_REG, value);
+        self.write_timer_reg(ESB_TIMER2_REG, value);
+        self.ping();
+        timeout
+    }
+
+    fn start(&self) {
+        self.ping();
+        self.device.write_config_u8(ESB_LOCK_REG, ESB_WDT_ENABLE);
+    }
+
+    fn stop(&self) -> Result<(), WatchdogError> {
+        self.ping();
+        self.device.write_config_u8(ESB_LOCK_REG, 0);
+        // The watchdog cannot be disabled after it is locked.
+        if self.device.read_config_u8(ESB_LOCK_REG) & ESB_WDT_ENABLE != 0 {
+            return Err(WatchdogError::Busy);
+        }
+        Ok(())
+    }
+
+    fn ping(&self) {
+        self.unlock_registers();
+        self.write_reload_reg(ESB_WDT_RELOAD);
+    }
+
+    fn boot_status(&self) -> WatchdogOptions {
+        self.boot_status
+    }
+}
@@ -0,0 +1,119 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The watchdog subsystem of Asterinas.
+//!
+//! A watchdog resets the system if it is not kept alive in time, so that the system can
+//! recover from hangs. Each watchdog is driven by a [`WatchdogDriver`] and registered as a
+//! [`Watchdog`], which implements the features common to all watchdogs (see its
+//! documentation). The kernel exposes the watchdogs as `/dev/watchdogN`, with the first
+//! one also as `/dev/watchdog`.
+//!
+//! The following drivers are provided:
+//!
+//! - `i6300esb`: the Intel 6300ESB watchdog on the PCI bus, which is emulated by QEMU
+//!   (`-device i6300esb`).
+//! - `dw_wdt`: the Synopsys DesignWare watchdog, which is used by many RISC-V SoCs. It is
+//!   discovered through the device tree.
+//! - `softdog`: a software watchdog that restarts the system by the SBI system reset
+//!   extension. It is registered on RISC-V if there is no hardware watchdog.
+//!
+//! The core relies on the system timer to ping the hardware on behalf of the user and to
+//! fire the pretimeout events.
+#![no_std]
+#![deny(unsafe_code)]
+
+extern crate alloc;
+
+#[cfg(target_arch = "riscv64")]
+mod dw_wdt;
+mod i6300esb;
+#[cfg(target_arch = "riscv64")]
+mod probe;
+#[cfg(target_arch = "riscv64")]
+mod softdog;
+mod watchdog;
+
+use alloc::{format, sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use component::{init_component, ComponentInitError};
+use log::info;
+use ostd::{
+    sync::{LocalIrqDisabled, SpinLock},
+    timer::{self, Jiffies},
+};
+
+pub use self::watchdog::{
+    set_pretimeout_governor, PretimeoutGovernor, Watchdog, WatchdogDriver, WatchdogError,
+    WatchdogInfo, WatchdogOptions, WATCHDOG_IDENTITY_LEN,
+};
+
+/// The interval (in milliseconds) between two polls of the watchdogs in the core.
+const POLL_INTERVAL_MS: u64 = 100;
+
+static WATCHDOGS: SpinLock<Vec<Arc<Watchdog>>, LocalIrqDisabled> = SpinLock::new(Vec::new());
+
+#[init_component]
+fn watchdog_component_init() -> Result<(), ComponentInitError> {
+    i6300esb::init();
+    #[cfg(target_arch = "riscv64")]
+    {
+        for driver in probe::probe_watchdogs() {
+            register_watchdog(driver);
+        }
+        if WATCHDOGS.lock().is_empty() {
+            register_watchdog(softdog::init());
+        }
+    }
+
+    timer::register_callback(poll_watchdogs);
+    Ok(())
+}
+
+/// Registers a watchdog driver, returning the registered watchdog.
+pub fn register_watchdog(driver: Arc<dyn WatchdogDriver>) -> Arc<Watchdog> {
+    let mut watchdogs = WATCHDOGS.lock();
+    let watchdog = Arc::new(Watchdog::new(watchdogs.len(), driver));
+    info!(
+        "[Watchdog]: registered watchdog{} with timeout {}s",
+        watchdog.index(),
+        watchdog.timeout()
+    );
+    watchdogs.push(watchdog.clone());
+    ostd::power::register_device(&format!("watchdog{}", watchdog.index()), watchdog.clone());
+    watchdog
+}
+
+/// Returns the watchdog with the index.
+pub fn get_watchdog(index: usize) -> Option<Arc<Watchdog>> {
+    WATCHDOGS.lock().get(index).cloned()
+}
+
+/// Returns all registered watchdogs.
+pub fn all_watchdogs() -> Vec<Arc<Watchdog>> {
+    WATCHDOGS.lock().clone()
+}
+
+/// Returns the time since boot in milliseconds.
+fn now_ms() -> u64 {
+    Jiffies::elapsed().as_duration().as_millis() as u64
+}
+
+fn poll_watchdogs() {
+    static LAST_POLL: AtomicU64 = AtomicU64::new(0);
+
+    let now = now_ms();
+    let last_poll = LAST_POLL.load(Ordering::Relaxed);
+    if now < last_poll + POLL_INTERVAL_MS
+        || LAST_POLL
+            .compare_exchange(last_poll, now, Ordering::Relaxed, Ordering::Relaxed)
+            .is_err()
+    {
+        return;
+    }
+
+    // Clone the list so that the pretimeout governors run without holding the lock.
+    for watchdog in all_watchdogs() {
+        watchdog.on_tick(now);
+    }
+}
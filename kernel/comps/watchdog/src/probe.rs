@@ -0,0 +1,63 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Discovery of watchdogs in the device tree.
+
+use alloc::{string::ToString, sync::Arc, vec::Vec};
+
+use log::{info, warn};
+use ostd::{arch::boot::DEVICE_TREE, io::MmioRegion};
+
+use crate::{dw_wdt::DwWdt, WatchdogDriver};
+
+/// The compatible strings of Synopsys DesignWare watchdogs.
+const DW_WDT_COMPATIBLES: &[&str] = &["snps,dw-wdt"];
+
+/// Probes the watchdogs described in the device tree.
+pub(crate) fn probe_watchdogs() -> Vec<Arc<dyn WatchdogDriver>> {
+    let mut drivers: Vec<Arc<dyn WatchdogDriver>> = Vec::new();
+
+    for node in DEVICE_TREE.get().unwrap().all_nodes() {
+        let Some(compatible) = node.compatible() else {
+            continue;
+        };
+        if !compatible.all().any(|c| DW_WDT_COMPATIBLES.contains(&c)) {
+            continue;
+        }
+        if node
+            .property("status")
+            .and_then(|prop| prop.as_str())
+            .is_some_and(|status| status != "okay" && status != "ok")
+        {
+            continue;
+        }
+
+        // The clock controllers are not supported, so the frequency must be given directly.
+        let Some(clock) = node
+            .property("clock-frequency")
+            .and_then(|prop| prop.as_usize())
+            .filter(|clock| *clock > 0)
+        else {
+            warn!("[Watchdog]: {}: unknown clock frequency", node.name);
+            continue;
+        };
+        let io_mem = match MmioRegion::claim_fdt_reg(&node) {
+            Ok(io_mem) => io_mem,
+            Err(err) => {
+                warn!(
+                    "[Watchdog]: {}: cannot claim the registers: {:?}",
+                    node.name, err
+                );
+                continue;
+            }
+        };
+
+        info!("[Watchdog]: found DesignWare watchdog {}", node.name);
+        drivers.push(Arc::new(DwWdt::new(
+            node.name.to_string(),
+            io_mem,
+            clock as u32,
+        )));
+    }
+
+    drivers
+}
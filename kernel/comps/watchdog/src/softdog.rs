@@ -0,0 +1,91 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The software watchdog.
+//!
+//! Like the `softdog` of Linux, the watchdog is a timer in the kernel, which restarts the
+//! system when it expires. The system is restarted by the SBI system reset extension (see
+//! [`ostd::arch::reboot`]). Since the timer is checked in the timer interrupt, the watchdog
+//! cannot recover the system if the timer interrupts are blocked.
+
+use alloc::sync::Arc;
+use core::{
+    ops::RangeInclusive,
+    sync::atomic::{AtomicU32, AtomicU64, Ordering},
+};
+
+use log::error;
+use ostd::timer;
+
+use crate::{now_ms, WatchdogDriver, WatchdogError};
+
+/// The deadline of a stopped watchdog.
+const NO_DEADLINE: u64 = u64::MAX;
+
+pub(crate) fn init() -> Arc<SoftDog> {
+    let softdog = Arc::new(SoftDog {
+        timeout: AtomicU32::new(0),
+        deadline: AtomicU64::new(NO_DEADLINE),
+    });
+
+    let cloned = softdog.clone();
+    timer::register_callback(move || cloned.check_expiry());
+    softdog
+}
+
+/// The software watchdog.
+#[derive(Debug)]
+pub(crate) struct SoftDog {
+    /// The timeout in seconds
+    timeout: AtomicU32,
+    /// The time (in milliseconds) when the watchdog expires
+    deadline: AtomicU64,
+}
+
+impl SoftDog {
+    fn check_expiry(&self) {
+        if now_ms() < self.deadline.load(Ordering::Relaxed) {
+            return;
+        }
+
+        error!("[Watchdog]: the software watchdog expired, restarting the system");
+        ostd::arch::reboot();
+    }
+}
+
+impl WatchdogDriver for SoftDog {
+    fn identity(&self) -> &str {
+        "Software Watchdog"
+    }
+
+    fn timeout_range(&self) -> RangeInclusive<u32> {
+        1..=u16::MAX as u32
+    }
+
+    fn set_timeout(&self, timeout: u32) -> u32 {
+        self.timeout.store(timeout, Ordering::Relaxed);
+        timeout
+    }
+
+    fn start(&self) {
+        self.ping();
+    }
+
+    fn stop(&self) -> Result<(), WatchdogError> {
+        self.deadline.store(NO_DEADLINE, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn ping(&self) {
+        let timeout = self.timeout.load(Ordering::Relaxed) as u64;
+        self.deadline
+            .store(now_ms() + timeout * 1000, Ordering::Relaxed);
+    }
+
+    fn time_left(&self) -> Option<u32> {
+        let deadline = self.deadline.load(Ordering::Relaxed);
+        if deadline == NO_DEADLINE {
+            return None;
+        }
+        Some((deadline.saturating_sub(now_ms()) / 1000) as u32)
+    }
+}
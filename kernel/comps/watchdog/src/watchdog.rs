@@ -0,0 +1,513 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The watchdog core.
+
+use alloc::sync::Arc;
+use core::{
+    fmt::Debug,
+    ops::RangeInclusive,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use bitflags::bitflags;
+use log::{info, warn};
+use ostd::{
+    power::DevicePm,
+    sync::{LocalIrqDisabled, SpinLock},
+};
+
+use crate::now_ms;
+
+/// The timeout (in seconds) that is used if the driver does not suggest one.
+const DEFAULT_TIMEOUT: u32 = 30;
+
+/// The maximum timeout (in seconds) that the user can set.
+///
+/// The timeouts longer than the maximum timeout of the hardware are implemented by pinging
+/// the hardware in the core, until no keepalive is received from the user for the timeout.
+const MAX_TIMEOUT: u32 = u16::MAX as u32;
+
+/// The maximum length of the identity reported to the user space, excluding the final `\0`.
+pub const WATCHDOG_IDENTITY_LEN: usize = 31;
+
+bitflags! {
+    /// The options and the status bits of watchdogs (`WDIOF_*` in Linux).
+    pub struct WatchdogOptions: u32 {
+        /// The system is reset because of overheating.
+        const OVERHEAT = 0x0001;
+        /// A fan has failed.
+        const FANFAULT = 0x0002;
+        /// The external relay 1 is triggered.
+        const EXTERN1 = 0x0004;
+        /// The external relay 2 is triggered.
+        const EXTERN2 = 0x0008;
+        /// The power is bad or failing.
+        const POWERUNDER = 0x0010;
+        /// The last reboot is caused by the watchdog.
+        const CARDRESET = 0x0020;
+        /// The power is overloaded.
+        const POWEROVER = 0x0040;
+        /// The timeout can be set.
+        const SETTIMEOUT = 0x0080;
+        /// The watchdog is stopped on closing only after "V" is written.
+        const MAGICCLOSE = 0x0100;
+        /// The pretimeout can be set.
+        const PRETIMEOUT = 0x0200;
+        /// The watchdog raises an alarm instead of resetting the system.
+        const ALARMONLY = 0x0400;
+        /// The watchdog can be kept alive by `WDIOC_KEEPALIVE`, or has been kept alive.
+        const KEEPALIVEPING = 0x8000;
+    }
+}
+
+/// The errors of watchdogs.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WatchdogError {
+    /// The parameters are invalid.
+    InvalidArgs,
+    /// The operation is not supported by the hardware.
+    NotSupported,
+    /// The watchdog is opened by others, or is locked by the hardware.
+    Busy,
+}
+
+/// The information of a watchdog (`struct watchdog_info` in Linux).
+#[derive(Clone, Copy, Debug)]
+pub struct WatchdogInfo {
+    /// The supported options
+    pub options: WatchdogOptions,
+    /// The version of the firmware
+    pub firmware_version: u32,
+    /// The identity, which is padded with zeros
+    pub identity: [u8; WATCHDOG_IDENTITY_LEN + 1],
+}
+
+/// A watchdog driver.
+///
+/// The methods may be called in the interrupt context, so they must not sleep.
+pub trait WatchdogDriver: Send + Sync + Debug {
+    /// Returns the identity of the watchdog, e.g., the name of the chip.
+    fn identity(&self) -> &str;
+
+    /// Returns the options supported by the driver.
+    ///
+    /// The options implemented by the core (i.e., [`WatchdogOptions::SETTIMEOUT`],
+    /// [`WatchdogOptions::MAGICCLOSE`], [`WatchdogOptions::PRETIMEOUT`], and
+    /// [`WatchdogOptions::KEEPALIVEPING`]) do not need to be included.
+    fn options(&self) -> WatchdogOptions {
+        WatchdogOptions::empty()
+    }
+
+    /// Returns the version of the firmware.
+    fn firmware_version(&self) -> u32 {
+        0
+    }
+
+    /// Returns the range of the timeouts (in seconds) supported by the hardware.
+    fn timeout_range(&self) -> RangeInclusive<u32>;
+
+    /// Sets the timeout (in seconds) of the hardware.
+    ///
+    /// The timeout is in [`Self::timeout_range`]. This method returns the actual timeout,
+    /// which may be rounded to a value supported by the hardware.
+    fn set_timeout(&self, timeout: u32) -> u32;
+
+    /// Starts the hardware.
+    fn start(&self);
+
+    /// Stops the hardware.
+    ///
+    /// This method fails with [`WatchdogError::NotSupported`] if the hardware cannot be
+    /// stopped once it is started, in which case the core keeps the hardware alive.
+    fn stop(&self) -> Result<(), WatchdogError>;
+
+    /// Pings the hardware to restart the countdown.
+    fn ping(&self);
+
+    /// Returns the seconds left before the hardware resets the system, if it can be read
+    /// from the hardware.
+    fn time_left(&self) -> Option<u32> {
+        None
+    }
+
+    /// Returns the status bits of the last reboot, e.g., [`WatchdogOptions::CARDRESET`].
+    fn boot_status(&self) -> WatchdogOptions {
+        WatchdogOptions::empty()
+    }
+
+    /// Returns whether the hardware has been started before the driver is loaded, e.g.,
+    /// by the firmware.
+    fn is_running(&self) -> bool {
+        false
+    }
+}
+
+/// The governor of the pretimeout events.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PretimeoutGovernor {
+    /// Logs the events.
+    Noop,
+    /// Panics the kernel, so that the logs are dumped before the system is reset.
+    Panic,
+}
+
+static PANICS_ON_PRETIMEOUT: AtomicBool = AtomicBool::new(false);
+
+/// Sets the governor of the pretimeout events for all watchdogs.
+pub fn set_pretimeout_governor(governor: PretimeoutGovernor) {
+    PANICS_ON_PRETIMEOUT.store(governor == PretimeoutGovernor::Panic, Ordering::Relaxed);
+}
+
+/// A registered watchdog.
+///
+/// Like Linux, the part that is common to all drivers is implemented here:
+///
+/// - The watchdog can be opened by only one user at a time. It is started on opening,
+///   and is stopped on closing only if the user writes "V" before closing (the magic close).
+/// - The pretimeout events are fired by the core at `pretimeout` seconds before the
+///   timeout, which does not require any support of the hardware.
+/// - The hardware is pinged by the core, if it cannot be stopped, or if its maximum
+///   timeout is shorter than the timeout set by the user.
+#[derive(Debug)]
+pub struct Watchdog {
+    index: usize,
+    driver: Arc<dyn WatchdogDriver>,
+    state: SpinLock<WatchdogState, LocalIrqDisabled>,
+    is_open: AtomicBool,
+}
+
+#[derive(Debug)]
+struct WatchdogState {
+    /// The timeout (in seconds) set by the user
+    timeout: u32,
+    /// The pretimeout (in seconds), or zero if the pretimeout events are disabled
+    pretimeout: u32,
+    /// The timeout (in seconds) of the hardware
+    hw_timeout: u32,
+    /// Whether the watchdog is started by the user
+    is_active: bool,
+    /// Whether the hardware is running
+    is_hw_running: bool,
+    /// Whether the watchdog will be stopped on closing
+    expects_close: bool,
+    /// Whether the watchdog has been kept alive since the status was read
+    was_kept_alive: bool,
+    /// Whether the pretimeout event has been fired since the last keepalive
+    has_fired_pretimeout: bool,
+    /// The time (in milliseconds) of the last keepalive from the user
+    last_keepalive: u64,
+    /// The time (in milliseconds) when the hardware was pinged
+    last_hw_ping: u64,
+    /// Whether the hardware was stopped by suspending the system, and will be restarted
+    is_hw_suspended: bool,
+}
+
+impl Watchdog {
+    pub(crate) fn new(index: usize, driver: Arc<dyn WatchdogDriver>) -> Self {
+        let range = driver.timeout_range();
+        let timeout = DEFAULT_TIMEOUT.clamp(*range.start(), *range.end());
+        let hw_timeout = driver.set_timeout(timeout);
+        let is_hw_running = driver.is_running();
+        if is_hw_running {
+            info!("[Watchdog]: watchdog{} is running, keeping it alive", index);
+            driver.ping();
+        }
+
+        let now = now_ms();
+        Self {
+            index,
+            driver,
+            state: SpinLock::new(WatchdogState {
+                timeout: hw_timeout,
+                pretimeout: 0,
+                hw_timeout,
+                is_active: false,
+                is_hw_running,
+                expects_close: false,
+                was_kept_alive: false,
+                has_fired_pretimeout: false,
+                last_keepalive: now,
+                last_hw_ping: now,
+                is_hw_suspended: false,
+            }),
+            is_open: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns the index of the watchdog.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the information of the watchdog.
+    pub fn info(&self) -> WatchdogInfo {
+        let mut identity = [0u8; WATCHDOG_IDENTITY_LEN + 1];
+        let name = self.driver.identity().as_bytes();
+        let len = name.len().min(WATCHDOG_IDENTITY_LEN);
+        identity[..len].copy_from_slice(&name[..len]);
+
+        WatchdogInfo {
+            options: self.driver.options()
+                | WatchdogOptions::SETTIMEOUT
+                | WatchdogOptions::MAGICCLOSE
+                | WatchdogOptions::PRETIMEOUT
+                | WatchdogOptions::KEEPALIVEPING,
+            firmware_version: self.driver.firmware_version(),
+            identity,
+        }
+    }
+
+    /// Opens the watchdog, which starts it.
+    ///
+    /// This method fails with [`WatchdogError::Busy`] if the watchdog has been opened.
+    pub fn open(&self) -> Result<(), WatchdogError> {
+        if self.is_open.swap(true, Ordering::Acquire) {
+            return Err(WatchdogError::Busy);
+        }
+        self.start();
+        Ok(())
+    }
+
+    /// Closes the watchdog.
+    ///
+    /// The watchdog is stopped if the magic close is expected. Otherwise, it keeps running,
+    /// and will reset the system if it is not opened and kept alive again in time.
+    pub fn close(&self) {
+        let expects_close = core::mem::take(&mut self.state.lock().expects_close);
+        if expects_close {
+            if self.stop().is_err() {
+                warn!("[Watchdog]: watchdog{} cannot be stopped", self.index);
+            }
+        } else if self.state.lock().is_active {
+            warn!("[Watchdog]: watchdog{} did not stop!", self.index);
+        }
+        self.is_open.store(false, Ordering::Release);
+    }
+
+    /// Sets whether the watchdog will be stopped on closing.
+    pub fn set_expects_close(&self, expects_close: bool) {
+        self.state.lock().expects_close = expects_close;
+    }
+
+    /// Starts the watchdog.
+    pub fn start(&self) {
+        let mut state = self.state.lock();
+        if state.is_active {
+            return;
+        }
+
+        let now = now_ms();
+        if state.is_hw_running {
+            self.driver.ping();
+        } else {
+            self.driver.start();
+        }
+        state.is_active = true;
+        state.is_hw_running = true;
+        state.has_fired_pretimeout = false;
+        state.last_keepalive = now;
+        state.last_hw_ping = now;
+    }
+
+    /// Stops the watchdog.
+    ///
+    /// If the hardware does not support stopping, the hardware is kept alive by the core, so
+    /// the watchdog is still stopped from the view of the user.
+    pub fn stop(&self) -> Result<(), WatchdogError> {
+        let mut state = self.state.lock();
+        if !state.is_active {
+            return Ok(());
+        }
+
+        match self.driver.stop() {
+            Ok(()) => state.is_hw_running = false,
+            Err(WatchdogError::NotSupported) => (),
+            Err(err) => return Err(err),
+        }
+        state.is_active = false;
+        Ok(())
+    }
+
+    /// Keeps the watchdog alive, if it is started.
+    pub fn keepalive(&self) {
+        let mut state = self.state.lock();
+        if !state.is_active {
+            return;
+        }
+
+        let now = now_ms();
+        self.driver.ping();
+        state.was_kept_alive = true;
+        state.has_fired_pretimeout = false;
+        state.last_keepalive = now;
+        state.last_hw_ping = now;
+    }
+
+    /// Returns the status bits of the watchdog.
+    ///
+    /// [`WatchdogOptions::KEEPALIVEPING`] is set if the watchdog has been kept alive since
+    /// the last call, and [`WatchdogOptions::MAGICCLOSE`] is set if the magic close is
+    /// expected.
+    pub fn status(&self) -> WatchdogOptions {
+        let mut state = self.state.lock();
+        let mut status = WatchdogOptions::empty();
+        if core::mem::take(&mut state.was_kept_alive) {
+            status |= WatchdogOptions::KEEPALIVEPING;
+        }
+        if state.expects_close {
+            status |= WatchdogOptions::MAGICCLOSE;
+        }
+        status
+    }
+
+    /// Returns the status bits of the last reboot.
+    pub fn boot_status(&self) -> WatchdogOptions {
+        self.driver.boot_status()
+    }
+
+    /// Returns the timeout in seconds.
+    pub fn timeout(&self) -> u32 {
+        self.state.lock().timeout
+    }
+
+    /// Sets the timeout in seconds and keeps the watchdog alive.
+    ///
+    /// This method returns the actual timeout, which may be rounded by the hardware.
+    pub fn set_timeout(&self, timeout: u32) -> Result<u32, WatchdogError> {
+        let range = self.driver.timeout_range();
+        if timeout < *range.start() || timeout > MAX_TIMEOUT {
+            return Err(WatchdogError::InvalidArgs);
+        }
+
+        let mut state = self.state.lock();
+        state.hw_timeout = self.driver.set_timeout(timeout.min(*range.end()));
+        state.timeout = if timeout > *range.end() {
+            timeout
+        } else {
+            state.hw_timeout
+        };
+        // Like Linux, the pretimeout is disabled if it becomes invalid.
+        if state.pretimeout >= state.timeout {
+            state.pretimeout = 0;
+        }
+        let timeout = state.timeout;
+        drop(state);
+
+        self.keepalive();
+        Ok(timeout)
+    }
+
+    /// Returns the pretimeout in seconds, or zero if the pretimeout events are disabled.
+    pub fn pretimeout(&self) -> u32 {
+        self.state.lock().pretimeout
+    }
+
+    /// Sets the pretimeout in seconds.
+    ///
+    /// The pretimeout event is fired when there are `pretimeout` seconds left before the
+    /// timeout. A zero pretimeout disables the events.
+    pub fn set_pretimeout(&self, pretimeout: u32) -> Result<(), WatchdogError> {
+        let mut state = self.state.lock();
+        if pretimeout != 0 && pretimeout >= state.timeout {
+            return Err(WatchdogError::InvalidArgs);
+        }
+        state.pretimeout = pretimeout;
+        state.has_fired_pretimeout = false;
+        Ok(())
+    }
+
+    /// Returns the seconds left before the system is reset.
+    pub fn time_left(&self) -> u32 {
+        let state = self.state.lock();
+        if !state.is_active {
+            return state.timeout;
+        }
+
+        let elapsed = now_ms().saturating_sub(state.last_keepalive);
+        let left = (state.timeout as u64 * 1000).saturating_sub(elapsed) / 1000;
+        if state.hw_timeout < state.timeout {
+            return left as u32;
+        }
+        self.driver.time_left().unwrap_or(left as u32)
+    }
+
+    /// Pings the hardware if needed and fires the pretimeout event if it is due.
+    ///
+    /// This method is called periodically in the interrupt context.
+    pub(crate) fn on_tick(&self, now: u64) {
+        let mut state = self.state.lock();
+        let ping_interval = state.hw_timeout as u64 * 1000 / 2;
+        let needs_ping = now.saturating_sub(state.last_hw_ping) >= ping_interval;
+
+        let mut fires_pretimeout = false;
+        if state.is_active {
+            let deadline = state.last_keepalive + state.timeout as u64 * 1000;
+            if state.pretimeout != 0
+                && !state.has_fired_pretimeout
+                && now + state.pretimeout as u64 * 1000 >= deadline
+            {
+                state.has_fired_pretimeout = true;
+                fires_pretimeout = true;
+            }
+
+            // Keep the hardware alive until the timeout set by the user expires.
+            if state.hw_timeout < state.timeout && now < deadline && needs_ping {
+                self.driver.ping();
+                state.last_hw_ping = now;
+            }
+        } else if state.is_hw_running && needs_ping {
+            self.driver.ping();
+            state.last_hw_ping = now;
+        }
+        drop(state);
+
+        if fires_pretimeout {
+            self.fire_pretimeout();
+        }
+    }
+
+    fn fire_pretimeout(&self) {
+        if PANICS_ON_PRETIMEOUT.load(Ordering::Relaxed) {
+            panic!("watchdog{}: pretimeout event", self.index);
+        }
+        info!("[Watchdog]: watchdog{}: pretimeout event", self.index);
+    }
+}
+
+/// The hardware is stopped during the system suspend, since nothing can keep it alive, and is
+/// restarted after the system resumes. The system cannot be suspended if the hardware is
+/// running but cannot be stopped.
+impl DevicePm for Watchdog {
+    fn suspend(&self) -> ostd::Result<()> {
+        let mut state = self.state.lock();
+        if !state.is_hw_running {
+            return Ok(());
+        }
+
+        if self.driver.stop().is_err() {
+            warn!(
+                "[Watchdog]: watchdog{} cannot be stopped to suspend",
+                self.index
+            );
+            return Err(ostd::Error::AccessDenied);
+        }
+        state.is_hw_running = false;
+        state.is_hw_suspended = true;
+        Ok(())
+    }
+
+    fn resume(&self) {
+        let mut state = self.state.lock();
+        if !core::mem::take(&mut state.is_hw_suspended) {
+            return;
+        }
+
+        // The user has no chance to keep the watchdog alive during the suspend.
+        let now = now_ms();
+        self.driver.start();
+        state.is_hw_running = true;
+        state.has_fired_pretimeout = false;
+        state.last_keepalive = now;
+        state.last_hw_ping = now;
+    }
+}
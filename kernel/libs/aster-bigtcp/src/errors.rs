@@ -9,6 +9,17 @@ pub enum BindError {
     InUse,
 }
 
+/// An error describing the reason why the configuration of an iface failed.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ConfigError {
+    /// The specified address or route already exists.
+    AlreadyExists,
+    /// The specified address or route does not exist.
+    NotFound,
+    /// The table of addresses or routes is full.
+    Exhausted,
+}
+
 pub mod tcp {
     /// An error returned by [`TcpListener::new_listen`].
     ///
@@ -112,3 +123,31 @@ pub mod udp {
         }
     }
 }
+
+pub mod raw {
+    /// An error returned by [`RawIpSocket::send`].
+    ///
+    /// [`RawIpSocket::send`]: crate::socket::RawIpSocket::send
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    pub enum SendError {
+        /// The IP version does not match the socket.
+        Unaddressable,
+        BufferFull,
+        /// The packet is too large.
+        TooLarge,
+    }
+}
+
+pub mod link {
+    /// An error returned by [`LinkSocket::send`].
+    ///
+    /// [`LinkSocket::send`]: crate::socket::LinkSocket::send
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    pub enum SendError {
+        /// The frame is too short to contain the link-layer header.
+        TooShort,
+        BufferFull,
+        /// The frame is too large.
+        TooLarge,
+    }
+}
@@ -1,6 +1,6 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use crate::{iface::ScheduleNextPoll, socket::SocketEventObserver};
+use crate::{filter::PacketFilter, iface::ScheduleNextPoll, socket::SocketEventObserver};
 
 /// Extension to be implemented by users of this crate.
 ///
@@ -19,4 +19,13 @@ pub trait Ext {
 
     /// The type for UDP sockets to observe events.
     type UdpEventObserver: SocketEventObserver;
+
+    /// The type for raw IP sockets to observe events.
+    type RawEventObserver: SocketEventObserver;
+
+    /// The type for link-layer sockets to observe events.
+    type LinkEventObserver: SocketEventObserver;
+
+    /// The type for ifaces to filter packets.
+    type PacketFilter: PacketFilter;
 }
@@ -0,0 +1,296 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Stateless packet filtering.
+//!
+//! Like the `filter` table of Linux netfilter, the packet filter consists of one chain per hook.
+//! Each chain contains a list of rules and a default policy. When a packet reaches a hook, the
+//! rules in the chain are checked in order, and the verdict of the first matching rule (or the
+//! policy of the chain, if no rules match) decides whether the packet is accepted or dropped.
+//!
+//! The filter is stateless, so there is no connection tracking and no NAT.
+
+use alloc::vec::Vec;
+use core::ops::RangeInclusive;
+
+use smoltcp::{
+    iface::packet::{IpPayload, Packet},
+    wire::{IpAddress, IpCidr, IpProtocol, IpRepr, IpVersion},
+};
+
+use crate::errors::ConfigError;
+
+/// The hooks where packets are filtered.
+///
+/// Since packets are never forwarded, a packet whose destination is not local is dropped after
+/// the prerouting hook. A packet whose destination is local traverses the output hook (if it is
+/// generated locally), the prerouting hook, and the input hook in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterHook {
+    /// Packets that are received, before the destination is checked.
+    Prerouting = 0,
+    /// Packets whose destination is local, before they are delivered to sockets.
+    Input = 1,
+    /// Packets that are generated locally, before they are sent.
+    Output = 2,
+}
+
+const NR_HOOKS: usize = 3;
+
+/// The verdict of a rule or the policy of a chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// The packet is accepted.
+    Accept,
+    /// The packet is dropped silently.
+    Drop,
+}
+
+/// The information of a packet that rules can match against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PacketInfo {
+    pub src_addr: IpAddress,
+    pub dst_addr: IpAddress,
+    pub protocol: IpProtocol,
+    /// The source port, if the packet is a TCP or UDP packet
+    pub src_port: Option<u16>,
+    /// The destination port, if the packet is a TCP or UDP packet
+    pub dst_port: Option<u16>,
+}
+
+impl PacketInfo {
+    /// Creates the information of a packet whose ports are known.
+    pub(crate) fn with_ports(ip_repr: &IpRepr, src_port: u16, dst_port: u16) -> Self {
+        Self {
+            src_addr: ip_repr.src_addr(),
+            dst_addr: ip_repr.dst_addr(),
+            protocol: ip_repr.next_header(),
+            src_port: Some(src_port),
+            dst_port: Some(dst_port),
+        }
+    }
+
+    /// Creates the information of a packet from its IP header and its IP payload.
+    ///
+    /// The ports are parsed from the IP payload for TCP and UDP packets. They are missing if the
+    /// IP payload is too short.
+    pub(crate) fn new(ip_repr: &IpRepr, ip_payload: &[u8]) -> Self {
+        let ports = match ip_repr.next_header() {
+            IpProtocol::Tcp | IpProtocol::Udp if ip_payload.len() >= 4 => Some((
+                u16::from_be_bytes([ip_payload[0], ip_payload[1]]),
+                u16::from_be_bytes([ip_payload[2], ip_payload[3]]),
+            )),
+            _ => None,
+        };
+
+        Self {
+            src_addr: ip_repr.src_addr(),
+            dst_addr: ip_repr.dst_addr(),
+            protocol: ip_repr.next_header(),
+            src_port: ports.map(|(src_port, _)| src_port),
+            dst_port: ports.map(|(_, dst_port)| dst_port),
+        }
+    }
+
+    /// Creates the information of an outgoing packet.
+    pub(crate) fn from_packet(pkt: &Packet) -> Self {
+        let ip_repr = pkt.ip_repr();
+
+        match pkt.payload() {
+            IpPayload::Tcp(tcp_repr) => {
+                Self::with_ports(&ip_repr, tcp_repr.src_port, tcp_repr.dst_port)
+            }
+            IpPayload::Udp(udp_repr, _) => {
+                Self::with_ports(&ip_repr, udp_repr.src_port, udp_repr.dst_port)
+            }
+            IpPayload::Raw(payload) => Self::new(&ip_repr, payload),
+            _ => Self::new(&ip_repr, &[]),
+        }
+    }
+}
+
+/// A rule that matches packets and decides their verdict.
+///
+/// A packet matches the rule if it matches all the specified criteria. Criteria that are `None`
+/// match all packets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilterRule {
+    pub ip_version: Option<IpVersion>,
+    pub protocol: Option<IpProtocol>,
+    pub src_cidr: Option<IpCidr>,
+    pub dst_cidr: Option<IpCidr>,
+    /// The range of the source ports, which only matches TCP and UDP packets
+    pub src_ports: Option<RangeInclusive<u16>>,
+    /// The range of the destination ports, which only matches TCP and UDP packets
+    pub dst_ports: Option<RangeInclusive<u16>>,
+    pub verdict: Verdict,
+}
+
+impl FilterRule {
+    /// Returns whether the packet matches the rule.
+    pub fn matches(&self, packet: &PacketInfo) -> bool {
+        fn port_matches(ports: &Option<RangeInclusive<u16>>, port: Option<u16>) -> bool {
+            match (ports, port) {
+                (None, _) => true,
+                (Some(ports), Some(port)) => ports.contains(&port),
+                (Some(_), None) => false,
+            }
+        }
+
+        self.ip_version
+            .is_none_or(|version| version == packet.src_addr.version())
+            && self
+                .protocol
+                .is_none_or(|protocol| protocol == packet.protocol)
+            && self
+                .src_cidr
+                .is_none_or(|cidr| cidr.contains_addr(&packet.src_addr))
+            && self
+                .dst_cidr
+                .is_none_or(|cidr| cidr.contains_addr(&packet.dst_addr))
+            && port_matches(&self.src_ports, packet.src_port)
+            && port_matches(&self.dst_ports, packet.dst_port)
+    }
+}
+
+/// The chains of rules for all the hooks.
+pub struct FilterTable {
+    chains: [FilterChain; NR_HOOKS],
+    next_handle: u32,
+}
+
+struct FilterChain {
+    policy: Verdict,
+    rules: Vec<(u32, FilterRule)>,
+}
+
+impl FilterChain {
+    const fn new() -> Self {
+        Self {
+            policy: Verdict::Accept,
+            rules: Vec::new(),
+        }
+    }
+}
+
+impl Default for FilterTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FilterTable {
+    /// Creates a table where all chains are empty and accept all packets.
+    pub const fn new() -> Self {
+        Self {
+            chains: [FilterChain::new(), FilterChain::new(), FilterChain::new()],
+            // Like Linux, the handle starts from one.
+            next_handle: 1,
+        }
+    }
+
+    /// Returns the policy of the chain.
+    pub fn policy(&self, hook: FilterHook) -> Verdict {
+        self.chains[hook as usize].policy
+    }
+
+    /// Sets the policy of the chain.
+    pub fn set_policy(&mut self, hook: FilterHook, policy: Verdict) {
+        self.chains[hook as usize].policy = policy;
+    }
+
+    /// Adds a rule to the chain and returns the handle of the rule.
+    ///
+    /// The rule is added to the end of the chain if `append` is true, or to the beginning of the
+    /// chain otherwise.
+    pub fn add_rule(
+        &mut self,
+        hook: FilterHook,
+        rule: FilterRule,
+        append: bool,
+    ) -> Result<u32, ConfigError> {
+        let chain = &mut self.chains[hook as usize];
+        if chain.rules.len() >= MAX_RULES_PER_CHAIN {
+            return Err(ConfigError::Exhausted);
+        }
+        let Some(next_handle) = self.next_handle.checked_add(1) else {
+            return Err(ConfigError::Exhausted);
+        };
+
+        let handle = core::mem::replace(&mut self.next_handle, next_handle);
+        if append {
+            chain.rules.push((handle, rule));
+        } else {
+            chain.rules.insert(0, (handle, rule));
+        }
+
+        Ok(handle)
+    }
+
+    /// Removes the rule whose handle is `handle` from the chain.
+    pub fn remove_rule(
+        &mut self,
+        hook: FilterHook,
+        handle: u32,
+    ) -> Result<FilterRule, ConfigError> {
+        let rules = &mut self.chains[hook as usize].rules;
+        let pos = rules
+            .iter()
+            .position(|(rule_handle, _)| *rule_handle == handle)
+            .ok_or(ConfigError::NotFound)?;
+
+        Ok(rules.remove(pos).1)
+    }
+
+    /// Removes all the rules from the chain.
+    pub fn flush(&mut self, hook: FilterHook) {
+        self.chains[hook as usize].rules.clear();
+    }
+
+    /// Returns the rules in the chain, along with their handles.
+    pub fn rules(&self, hook: FilterHook) -> impl Iterator<Item = (u32, &FilterRule)> {
+        self.chains[hook as usize]
+            .rules
+            .iter()
+            .map(|(handle, rule)| (*handle, rule))
+    }
+
+    /// Decides the verdict of the packet at the hook.
+    pub fn evaluate(&self, hook: FilterHook, packet: &PacketInfo) -> Verdict {
+        let chain = &self.chains[hook as usize];
+
+        chain
+            .rules
+            .iter()
+            .find(|(_, rule)| rule.matches(packet))
+            .map_or(chain.policy, |(_, rule)| rule.verdict)
+    }
+}
+
+/// The maximum number of rules in a chain.
+const MAX_RULES_PER_CHAIN: usize = 1024;
+
+/// A packet filter that decides the verdict of packets at the hooks.
+///
+/// This is usually implemented by looking up the rules in a global [`FilterTable`].
+pub trait PacketFilter {
+    /// Decides the verdict of the packet at the hook.
+    fn filter(hook: FilterHook, packet: &PacketInfo) -> Verdict;
+}
+
+/// Returns whether the packet is accepted at the hook.
+pub(crate) fn is_accepted<F: PacketFilter>(hook: FilterHook, packet: &PacketInfo) -> bool {
+    F::filter(hook, packet) == Verdict::Accept
+}
+
+/// Returns whether a locally generated packet whose destination is local is accepted.
+///
+/// Such a packet traverses the output hook, the prerouting hook, and the input hook in order.
+pub(crate) fn is_local_accepted<F: PacketFilter>(packet: &PacketInfo) -> bool {
+    [
+        FilterHook::Output,
+        FilterHook::Prerouting,
+        FilterHook::Input,
+    ]
+    .into_iter()
+    .all(|hook| is_accepted::<F>(hook, packet))
+}
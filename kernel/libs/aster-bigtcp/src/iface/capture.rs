@@ -0,0 +1,185 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::sync::Arc;
+
+use smoltcp::{
+    phy::{Device, Medium, RxToken, TxToken},
+    time::Instant,
+    wire::{EthernetFrame, IpVersion},
+};
+
+use crate::{
+    ext::Ext,
+    socket::{FrameDirection, LinkSocketBg},
+    socket_table::SocketTable,
+};
+
+/// A device that delivers a copy of every frame to the link-layer sockets.
+///
+/// Frames are captured when the RX tokens or the TX tokens are consumed, so the ifaces do not
+/// need to know about the link-layer sockets.
+pub(super) struct CaptureDevice<'a, D: ?Sized, E: Ext> {
+    device: &'a mut D,
+    sockets: &'a SocketTable<E>,
+    medium: Medium,
+}
+
+/// An RX token whose frame will be captured when it is consumed.
+pub(super) struct CaptureRxToken<'a, T, E: Ext> {
+    token: T,
+    sockets: &'a SocketTable<E>,
+    medium: Medium,
+}
+
+/// A TX token whose frame will be captured when it is consumed.
+pub(super) struct CaptureTxToken<'a, T, E: Ext> {
+    token: T,
+    sockets: &'a SocketTable<E>,
+    medium: Medium,
+}
+
+impl<'a, D: Device + ?Sized, E: Ext> CaptureDevice<'a, D, E> {
+    pub(super) fn new(device: &'a mut D, sockets: &'a SocketTable<E>) -> Self {
+        let medium = device.capabilities().medium;
+        Self {
+            device,
+            sockets,
+            medium,
+        }
+    }
+
+    pub(super) fn receive(
+        &mut self,
+        timestamp: Instant,
+    ) -> Option<(
+        CaptureRxToken<'_, D::RxToken<'_>, E>,
+        CaptureTxToken<'_, D::TxToken<'_>, E>,
+    )> {
+        let (rx_token, tx_token) = self.device.receive(timestamp)?;
+        Some((
+            CaptureRxToken {
+                token: rx_token,
+                sockets: self.sockets,
+                medium: self.medium,
+            },
+            CaptureTxToken {
+                token: tx_token,
+                sockets: self.sockets,
+                medium: self.medium,
+            },
+        ))
+    }
+
+    pub(super) fn transmit(
+        &mut self,
+        timestamp: Instant,
+    ) -> Option<CaptureTxToken<'_, D::TxToken<'_>, E>> {
+        let tx_token = self.device.transmit(timestamp)?;
+        Some(CaptureTxToken {
+            token: tx_token,
+            sockets: self.sockets,
+            medium: self.medium,
+        })
+    }
+
+    /// Transmits the frames that are queued in the link-layer sockets.
+    ///
+    /// Like Linux, a transmitted frame is captured by all link-layer sockets except the socket
+    /// that sends the frame.
+    pub(super) fn dispatch_link(&mut self, timestamp: Instant) {
+        let sockets = self.sockets;
+
+        for socket in sockets.link_socket_iter() {
+            while socket.need_dispatch() {
+                let Some(tx_token) = self.device.transmit(timestamp) else {
+                    return;
+                };
+
+                socket.dispatch(|frame| {
+                    tx_token.consume(frame.len(), |buffer| buffer.copy_from_slice(frame));
+                    capture(
+                        sockets,
+                        self.medium,
+                        frame,
+                        FrameDirection::Outgoing,
+                        Some(socket),
+                    );
+                });
+            }
+        }
+    }
+}
+
+impl<T: RxToken, E: Ext> RxToken for CaptureRxToken<'_, T, E> {
+    fn consume<R, F>(self, f: F) -> R
+    where
+        F: FnOnce(&[u8]) -> R,
+    {
+        let Self {
+            token,
+            sockets,
+            medium,
+        } = self;
+
+        token.consume(|frame| {
+            capture(sockets, medium, frame, FrameDirection::Incoming, None);
+            f(frame)
+        })
+    }
+}
+
+impl<T: TxToken, E: Ext> TxToken for CaptureTxToken<'_, T, E> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let Self {
+            token,
+            sockets,
+            medium,
+        } = self;
+
+        token.consume(len, |buffer| {
+            let res = f(buffer);
+            capture(sockets, medium, buffer, FrameDirection::Outgoing, None);
+            res
+        })
+    }
+}
+
+/// Delivers a copy of the frame to the link-layer sockets, except `sender`.
+pub(super) fn capture<E: Ext>(
+    sockets: &SocketTable<E>,
+    medium: Medium,
+    frame: &[u8],
+    direction: FrameDirection,
+    sender: Option<&Arc<LinkSocketBg<E>>>,
+) {
+    let mut link_sockets = sockets
+        .link_socket_iter()
+        .filter(|socket| !sender.is_some_and(|sender| Arc::ptr_eq(socket, sender)))
+        .peekable();
+    if link_sockets.peek().is_none() {
+        return;
+    }
+
+    let ether_type = match medium {
+        Medium::Ethernet => EthernetFrame::new_checked(frame)
+            .ok()
+            .map(|frame| u16::from(frame.ethertype())),
+        Medium::Ip => match IpVersion::of_packet(frame) {
+            Ok(IpVersion::Ipv4) => Some(ETH_P_IP),
+            Ok(IpVersion::Ipv6) => Some(ETH_P_IPV6),
+            Err(_) => None,
+        },
+    };
+
+    for socket in link_sockets {
+        socket.capture(frame, ether_type, direction);
+    }
+}
+
+/// The EtherType of IPv4 packets.
+const ETH_P_IP: u16 = 0x0800;
+/// The EtherType of IPv6 packets.
+const ETH_P_IPV6: u16 = 0x86DD;
@@ -6,30 +6,38 @@ use alloc::{
     sync::Arc,
     vec::Vec,
 };
+use core::sync::atomic::{AtomicU32, Ordering};
 
 use ostd::sync::{LocalIrqDisabled, SpinLock, SpinLockGuard};
 use smoltcp::{
     iface::{packet::Packet, Context},
     phy::Device,
-    wire::{IpAddress, IpEndpoint, Ipv4Address, Ipv4Packet},
+    wire::{HardwareAddress, IpAddress, IpEndpoint, Ipv4Address, Ipv4Cidr, Ipv6Address, Ipv6Cidr},
 };
 
 use super::{
-    poll::{FnHelper, PollContext, SocketTableAction},
+    capture::{CaptureDevice, CaptureTxToken},
+    info::{IfaceRoute, InterfaceFlags, InterfaceType},
+    poll::{FnHelper, IpPacket, PollContext, SocketTableAction},
     poll_iface::PollableIface,
     port::BindPortConfig,
     time::get_network_timestamp,
     Iface,
 };
 use crate::{
-    errors::BindError,
+    errors::{BindError, ConfigError},
     ext::Ext,
-    socket::{TcpListenerBg, UdpSocketBg},
+    filter::{is_accepted, FilterHook, PacketInfo},
+    socket::{LinkSocketBg, RawIpSocketBg, TcpListenerBg, UdpSocketBg},
     socket_table::SocketTable,
 };
 
 pub struct IfaceCommon<E: Ext> {
+    index: u32,
     name: String,
+    type_: InterfaceType,
+    flags: InterfaceFlags,
+    mtu: usize,
     interface: SpinLock<PollableIface<E>, LocalIrqDisabled>,
     used_ports: SpinLock<BTreeMap<u16, usize>, LocalIrqDisabled>,
     sockets: SpinLock<SocketTable<E>, LocalIrqDisabled>,
@@ -39,11 +47,21 @@ pub struct IfaceCommon<E: Ext> {
 impl<E: Ext> IfaceCommon<E> {
     pub(super) fn new(
         name: String,
+        type_: InterfaceType,
+        flags: InterfaceFlags,
+        mtu: usize,
         interface: smoltcp::iface::Interface,
         sched_poll: E::ScheduleNextPoll,
     ) -> Self {
+        // Like Linux, the index starts from one. Zero is never a valid index.
+        static NEXT_INDEX: AtomicU32 = AtomicU32::new(1);
+
         Self {
+            index: NEXT_INDEX.fetch_add(1, Ordering::Relaxed),
             name,
+            type_,
+            flags,
+            mtu,
             interface: SpinLock::new(PollableIface::new(interface)),
             used_ports: SpinLock::new(BTreeMap::new()),
             sockets: SpinLock::new(SocketTable::new()),
@@ -51,14 +69,74 @@ impl<E: Ext> IfaceCommon<E> {
         }
     }
 
+    pub(super) fn index(&self) -> u32 {
+        self.index
+    }
+
     pub(super) fn name(&self) -> &str {
         &self.name
     }
 
+    pub(super) fn type_(&self) -> InterfaceType {
+        self.type_
+    }
+
+    pub(super) fn flags(&self) -> InterfaceFlags {
+        self.flags
+    }
+
+    pub(super) fn mtu(&self) -> usize {
+        self.mtu
+    }
+
+    pub(super) fn hardware_addr(&self) -> HardwareAddress {
+        self.interface.lock().hardware_addr()
+    }
+
     pub(super) fn ipv4_addr(&self) -> Option<Ipv4Address> {
         self.interface.lock().ipv4_addr()
     }
 
+    pub(super) fn ipv4_cidrs(&self) -> Vec<Ipv4Cidr> {
+        self.interface.lock().ipv4_cidrs()
+    }
+
+    pub(super) fn add_ipv4_cidr(&self, ipv4_cidr: Ipv4Cidr) -> Result<(), ConfigError> {
+        self.interface.lock().add_ipv4_cidr(ipv4_cidr)
+    }
+
+    pub(super) fn remove_ipv4_addr(&self, ipv4_addr: Ipv4Address) -> Result<Ipv4Cidr, ConfigError> {
+        self.interface.lock().remove_ipv4_addr(ipv4_addr)
+    }
+
+    pub(super) fn ipv6_cidrs(&self) -> Vec<Ipv6Cidr> {
+        self.interface.lock().ipv6_cidrs()
+    }
+
+    pub(super) fn add_ipv6_cidr(&self, ipv6_cidr: Ipv6Cidr) -> Result<(), ConfigError> {
+        self.interface.lock().add_ipv6_cidr(ipv6_cidr)
+    }
+
+    pub(super) fn remove_ipv6_addr(&self, ipv6_addr: Ipv6Address) -> Result<Ipv6Cidr, ConfigError> {
+        self.interface.lock().remove_ipv6_addr(ipv6_addr)
+    }
+
+    pub(super) fn set_ipv6_default_route(&self, router: Ipv6Address) -> Result<(), ConfigError> {
+        self.interface.lock().set_ipv6_default_route(router)
+    }
+
+    pub(super) fn ipv4_routes(&self) -> Vec<IfaceRoute> {
+        self.interface.lock().ipv4_routes()
+    }
+
+    pub(super) fn add_ipv4_route(&self, route: IfaceRoute) -> Result<(), ConfigError> {
+        self.interface.lock().add_ipv4_route(route)
+    }
+
+    pub(super) fn remove_ipv4_route(&self, cidr: Ipv4Cidr) -> Result<IfaceRoute, ConfigError> {
+        self.interface.lock().remove_ipv4_route(cidr)
+    }
+
     pub(super) fn sched_poll(&self) -> &E::ScheduleNextPoll {
         &self.sched_poll
     }
@@ -84,10 +162,11 @@ impl<E: Ext> IfaceCommon<E> {
     pub(super) fn bind(
         &self,
         iface: Arc<dyn Iface<E>>,
+        addr: IpAddress,
         config: BindPortConfig,
     ) -> core::result::Result<BoundPort<E>, BindError> {
         let port = self.bind_port(config)?;
-        Ok(BoundPort { iface, port })
+        Ok(BoundPort { iface, addr, port })
     }
 
     /// Allocates an unused ephemeral port.
@@ -149,6 +228,16 @@ impl<E: Ext> IfaceCommon<E> {
         sockets.insert_udp_socket(socket);
     }
 
+    pub(crate) fn register_raw_socket(&self, socket: Arc<RawIpSocketBg<E>>) {
+        let mut sockets = self.sockets.lock();
+        sockets.insert_raw_socket(socket);
+    }
+
+    pub(crate) fn register_link_socket(&self, socket: Arc<LinkSocketBg<E>>) {
+        let mut sockets = self.sockets.lock();
+        sockets.insert_link_socket(socket);
+    }
+
     pub(crate) fn remove_tcp_listener(&self, socket: &Arc<TcpListenerBg<E>>) {
         let mut sockets = self.sockets.lock();
         let removed = sockets.remove_listener(socket.listener_key());
@@ -160,6 +249,18 @@ impl<E: Ext> IfaceCommon<E> {
         let removed = sockets.remove_udp_socket(socket);
         debug_assert!(removed.is_some());
     }
+
+    pub(crate) fn remove_raw_socket(&self, socket: &Arc<RawIpSocketBg<E>>) {
+        let mut sockets = self.sockets.lock();
+        let removed = sockets.remove_raw_socket(socket);
+        debug_assert!(removed.is_some());
+    }
+
+    pub(crate) fn remove_link_socket(&self, socket: &Arc<LinkSocketBg<E>>) {
+        let mut sockets = self.sockets.lock();
+        let removed = sockets.remove_link_socket(socket);
+        debug_assert!(removed.is_some());
+    }
 }
 
 impl<E: Ext> IfaceCommon<E> {
@@ -174,20 +275,40 @@ impl<E: Ext> IfaceCommon<E> {
         P: for<'pkt, 'cx, 'tx> FnHelper<
             &'pkt [u8],
             &'cx mut Context,
-            D::TxToken<'tx>,
-            Option<(Ipv4Packet<&'pkt [u8]>, D::TxToken<'tx>)>,
+            CaptureTxToken<'tx, D::TxToken<'tx>, E>,
+            Option<(
+                IpPacket<&'pkt [u8]>,
+                CaptureTxToken<'tx, D::TxToken<'tx>, E>,
+            )>,
         >,
-        Q: FnMut(&Packet, &mut Context, D::TxToken<'_>),
+        Q: FnMut(&Packet, &mut Context, CaptureTxToken<'_, D::TxToken<'_>, E>),
     {
         let mut interface = self.interface();
-        interface.context_mut().now = get_network_timestamp();
+        let now = get_network_timestamp();
+        interface.context_mut().now = now;
 
         let mut sockets = self.sockets.lock();
         let mut socket_actions = Vec::new();
 
+        // Frames from link-layer sockets are transmitted first, so that they can be processed
+        // below if they are looped back by the device.
+        let mut device = CaptureDevice::new(device, &sockets);
+        device.dispatch_link(now);
+
+        // Packets that are sent to the device are filtered at the output hook.
+        let mut dispatch_phy =
+            |pkt: &Packet<'_>,
+             cx: &mut Context,
+             tx_token: CaptureTxToken<'_, D::TxToken<'_>, E>| {
+                if is_accepted::<E::PacketFilter>(FilterHook::Output, &PacketInfo::from_packet(pkt))
+                {
+                    dispatch_phy(pkt, cx, tx_token);
+                }
+            };
+
         let mut context = PollContext::new(interface.as_mut(), &sockets, &mut socket_actions);
-        context.poll_ingress(device, &mut process_phy, &mut dispatch_phy);
-        context.poll_egress(device, &mut dispatch_phy);
+        context.poll_ingress(&mut device, &mut process_phy, &mut dispatch_phy);
+        context.poll_egress(&mut device, &mut dispatch_phy);
 
         // Insert new connections and remove dead connections.
         for action in socket_actions.into_iter() {
@@ -215,6 +336,7 @@ impl<E: Ext> IfaceCommon<E> {
 // FIXME: TCP and UDP ports are independent. Find a way to track the protocol here.
 pub struct BoundPort<E: Ext> {
     iface: Arc<dyn Iface<E>>,
+    addr: IpAddress,
     port: u16,
 }
 
@@ -230,12 +352,8 @@ impl<E: Ext> BoundPort<E> {
     }
 
     /// Returns the bound endpoint.
-    pub fn endpoint(&self) -> Option<IpEndpoint> {
-        let ip_addr = {
-            let ipv4_addr = self.iface().ipv4_addr()?;
-            IpAddress::Ipv4(ipv4_addr)
-        };
-        Some(IpEndpoint::new(ip_addr, self.port))
+    pub fn endpoint(&self) -> IpEndpoint {
+        IpEndpoint::new(self.addr, self.port)
     }
 }
 
@@ -1,11 +1,18 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use alloc::sync::Arc;
+use alloc::{sync::Arc, vec::Vec};
 
-use smoltcp::wire::Ipv4Address;
+use smoltcp::wire::{HardwareAddress, IpAddress, Ipv4Address, Ipv4Cidr, Ipv6Address, Ipv6Cidr};
 
-use super::{port::BindPortConfig, BoundPort};
-use crate::{errors::BindError, ext::Ext};
+use super::{
+    info::{IfaceRoute, InterfaceFlags, InterfaceType},
+    port::BindPortConfig,
+    BoundPort,
+};
+use crate::{
+    errors::{BindError, ConfigError},
+    ext::Ext,
+};
 
 /// A network interface.
 ///
@@ -21,6 +28,9 @@ pub trait Iface<E>: internal::IfaceInternal<E> + Send + Sync {
 impl<E: Ext> dyn Iface<E> {
     /// Binds a socket to the iface.
     ///
+    /// The socket will use `addr` as its local address, which should be one of the addresses of
+    /// the iface.
+    ///
     /// After binding the socket to the iface, the iface will handle all packets to and from the
     /// socket.
     ///
@@ -32,10 +42,18 @@ impl<E: Ext> dyn Iface<E> {
     /// <https://github.com/smoltcp-rs/smoltcp/issues/779>.
     pub fn bind(
         self: &Arc<Self>,
+        addr: IpAddress,
         config: BindPortConfig,
     ) -> core::result::Result<BoundPort<E>, BindError> {
         let common = self.common();
-        common.bind(self.clone(), config)
+        common.bind(self.clone(), addr, config)
+    }
+
+    /// Gets the index of the iface.
+    ///
+    /// The index is unique among all ifaces and is never zero.
+    pub fn index(&self) -> u32 {
+        self.common().index()
     }
 
     /// Gets the name of the iface.
@@ -45,13 +63,87 @@ impl<E: Ext> dyn Iface<E> {
         self.common().name()
     }
 
+    /// Gets the hardware type of the iface.
+    pub fn type_(&self) -> InterfaceType {
+        self.common().type_()
+    }
+
+    /// Gets the flags of the iface.
+    pub fn flags(&self) -> InterfaceFlags {
+        self.common().flags()
+    }
+
+    /// Gets the maximum transmission unit (MTU) of the iface.
+    ///
+    /// The MTU is the maximum size of IP packets, excluding the link-layer headers.
+    pub fn mtu(&self) -> usize {
+        self.common().mtu()
+    }
+
+    /// Gets the hardware address of the iface.
+    pub fn hardware_addr(&self) -> HardwareAddress {
+        self.common().hardware_addr()
+    }
+
     /// Gets the IPv4 address of the iface, if any.
     ///
-    /// FIXME: One iface may have multiple IPv4 addresses.
+    /// If the iface has multiple IPv4 addresses, the first one is the primary address, which
+    /// is returned by this method.
     pub fn ipv4_addr(&self) -> Option<Ipv4Address> {
         self.common().ipv4_addr()
     }
 
+    /// Gets all the IPv4 addresses of the iface, along with their prefix lengths.
+    pub fn ipv4_cidrs(&self) -> Vec<Ipv4Cidr> {
+        self.common().ipv4_cidrs()
+    }
+
+    /// Adds an IPv4 address to the iface.
+    pub fn add_ipv4_cidr(&self, ipv4_cidr: Ipv4Cidr) -> Result<(), ConfigError> {
+        self.common().add_ipv4_cidr(ipv4_cidr)
+    }
+
+    /// Removes an IPv4 address from the iface.
+    ///
+    /// This method returns the removed address, along with its prefix length.
+    pub fn remove_ipv4_addr(&self, ipv4_addr: Ipv4Address) -> Result<Ipv4Cidr, ConfigError> {
+        self.common().remove_ipv4_addr(ipv4_addr)
+    }
+
+    /// Gets all the IPv6 addresses of the iface, along with their prefix lengths.
+    pub fn ipv6_cidrs(&self) -> Vec<Ipv6Cidr> {
+        self.common().ipv6_cidrs()
+    }
+
+    /// Adds an IPv6 address to the iface.
+    pub fn add_ipv6_cidr(&self, ipv6_cidr: Ipv6Cidr) -> Result<(), ConfigError> {
+        self.common().add_ipv6_cidr(ipv6_cidr)
+    }
+
+    /// Removes an IPv6 address from the iface.
+    ///
+    /// This method returns the removed address, along with its prefix length.
+    pub fn remove_ipv6_addr(&self, ipv6_addr: Ipv6Address) -> Result<Ipv6Cidr, ConfigError> {
+        self.common().remove_ipv6_addr(ipv6_addr)
+    }
+
+    /// Gets all the IPv4 routes via gateways of the iface.
+    pub fn ipv4_routes(&self) -> Vec<IfaceRoute> {
+        self.common().ipv4_routes()
+    }
+
+    /// Adds a route via a gateway to the iface.
+    pub fn add_ipv4_route(&self, route: IfaceRoute) -> Result<(), ConfigError> {
+        self.common().add_ipv4_route(route)
+    }
+
+    /// Removes the route to the destination network from the iface.
+    ///
+    /// This method returns the removed route.
+    pub fn remove_ipv4_route(&self, cidr: Ipv4Cidr) -> Result<IfaceRoute, ConfigError> {
+        self.common().remove_ipv4_route(cidr)
+    }
+
     /// Returns a reference to the associated [`ScheduleNextPoll`].
     pub fn sched_poll(&self) -> &E::ScheduleNextPoll {
         self.common().sched_poll()
@@ -0,0 +1,56 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use smoltcp::wire::{Ipv4Address, Ipv4Cidr};
+
+/// The hardware type of an iface.
+///
+/// The values are the same as the `ARPHRD_*` constants in Linux, see
+/// <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/if_arp.h#L30>.
+#[repr(u16)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterfaceType {
+    /// Ethernet 10Mbps
+    Ether = 1,
+    /// Loopback device
+    Loopback = 772,
+    /// No link-layer header (e.g., TUN devices)
+    None = 0xFFFE,
+}
+
+bitflags::bitflags! {
+    /// The flags of an iface.
+    ///
+    /// The values are the same as the `IFF_*` constants in Linux, see
+    /// <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/if.h#L82>.
+    pub struct InterfaceFlags: u32 {
+        /// The iface is up.
+        const UP = 1 << 0;
+        /// The broadcast address is valid.
+        const BROADCAST = 1 << 1;
+        /// The iface is a loopback iface.
+        const LOOPBACK = 1 << 3;
+        /// The iface is a point-to-point link.
+        const POINTOPOINT = 1 << 4;
+        /// The driver signals that the link is up.
+        const RUNNING = 1 << 6;
+        /// The iface has no ARP protocol.
+        const NOARP = 1 << 7;
+        /// The iface receives all packets.
+        const PROMISC = 1 << 8;
+        /// The iface supports multicast.
+        const MULTICAST = 1 << 12;
+        /// The driver signals that the L1 is up.
+        const LOWER_UP = 1 << 16;
+    }
+}
+
+/// A route via a gateway.
+///
+/// Packets sent to addresses in `cidr` are forwarded to `gateway`. Note that routes to directly
+/// connected networks are implied by the IP addresses of the iface and are not represented by
+/// this type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IfaceRoute {
+    pub cidr: Ipv4Cidr,
+    pub gateway: Ipv4Address,
+}
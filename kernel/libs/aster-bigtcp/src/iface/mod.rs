@@ -1,8 +1,10 @@
 // SPDX-License-Identifier: MPL-2.0
 
+mod capture;
 mod common;
 #[expect(clippy::module_inception)]
 mod iface;
+mod info;
 mod phy;
 mod poll;
 mod poll_iface;
@@ -12,6 +14,7 @@ mod time;
 
 pub use common::BoundPort;
 pub use iface::Iface;
+pub use info::{IfaceRoute, InterfaceFlags, InterfaceType};
 pub use phy::{EtherIface, IpIface};
 pub(crate) use poll_iface::{PollKey, PollableIfaceMut};
 pub use port::BindPortConfig;
@@ -1,14 +1,21 @@
 // SPDX-License-Identifier: MPL-2.0
 
 use alloc::{collections::btree_map::BTreeMap, string::String, sync::Arc};
+use core::sync::atomic::{AtomicBool, Ordering};
 
 use ostd::sync::{LocalIrqDisabled, SpinLock};
 use smoltcp::{
-    iface::{packet::Packet, Config, Context},
-    phy::{DeviceCapabilities, TxToken},
+    iface::{
+        packet::{IpPayload, Packet},
+        Config, Context,
+    },
+    phy::{Device, DeviceCapabilities, Medium, TxToken},
+    time::Duration,
     wire::{
         self, ArpOperation, ArpPacket, ArpRepr, EthernetAddress, EthernetFrame, EthernetProtocol,
-        EthernetRepr, IpAddress, Ipv4Address, Ipv4AddressExt, Ipv4Cidr, Ipv4Packet,
+        EthernetRepr, HardwareAddress, Icmpv6Packet, Icmpv6Repr, IpAddress, IpProtocol,
+        Ipv4Address, Ipv4AddressExt, Ipv4Cidr, Ipv4Packet, Ipv6Address, Ipv6Cidr, Ipv6Packet,
+        Ipv6Repr, NdiscNeighborFlags, NdiscPrefixInfoFlags, NdiscRepr, RawHardwareAddress,
     },
 };
 
@@ -16,8 +23,8 @@ use crate::{
     device::{NotifyDevice, WithDevice},
     ext::Ext,
     iface::{
-        common::IfaceCommon, iface::internal::IfaceInternal, time::get_network_timestamp, Iface,
-        ScheduleNextPoll,
+        common::IfaceCommon, iface::internal::IfaceInternal, poll::IpPacket,
+        time::get_network_timestamp, Iface, InterfaceFlags, InterfaceType, ScheduleNextPoll,
     },
 };
 
@@ -25,41 +32,94 @@ pub struct EtherIface<D, E: Ext> {
     driver: D,
     common: IfaceCommon<E>,
     ether_addr: EthernetAddress,
+    link_local_addr: Ipv6Address,
     arp_table: SpinLock<BTreeMap<Ipv4Address, EthernetAddress>, LocalIrqDisabled>,
+    ndp_table: SpinLock<BTreeMap<Ipv6Address, EthernetAddress>, LocalIrqDisabled>,
+    /// Whether a Router Solicitation message has been sent
+    is_router_solicited: AtomicBool,
+    /// The configuration learned from the last Router Advertisement message, which has not been
+    /// applied to the iface yet
+    pending_router_config: SpinLock<Option<RouterConfig>, LocalIrqDisabled>,
+}
+
+/// The configuration learned from a Router Advertisement message.
+#[derive(Debug, Clone, Copy)]
+struct RouterConfig {
+    /// The default router, if the router can be used as a default router
+    default_router: Option<Ipv6Address>,
+    /// The address generated by stateless address autoconfiguration (SLAAC)
+    slaac_cidr: Option<Ipv6Cidr>,
+}
+
+/// A link-layer packet that is generated when processing or dispatching IP packets.
+enum LinkPacket {
+    Arp(ArpRepr),
+    Ndisc {
+        dst_ether: EthernetAddress,
+        ip_pkt: Packet<'static>,
+    },
 }
 
 impl<D: WithDevice, E: Ext> EtherIface<D, E> {
     pub fn new(
         driver: D,
         ether_addr: EthernetAddress,
-        ip_cidr: Ipv4Cidr,
-        gateway: Ipv4Address,
+        ip_cidr: Option<Ipv4Cidr>,
+        gateway: Option<Ipv4Address>,
         name: String,
         sched_poll: E::ScheduleNextPoll,
     ) -> Arc<Self> {
-        let interface = driver.with(|device| {
+        let link_local_addr = eui64_addr(&LINK_LOCAL_PREFIX, &ether_addr);
+
+        let (interface, mtu) = driver.with(|device| {
             let config = Config::new(wire::HardwareAddress::Ethernet(ether_addr));
             let now = get_network_timestamp();
 
             let mut interface = smoltcp::iface::Interface::new(config, device, now);
             interface.update_ip_addrs(|ip_addrs| {
                 debug_assert!(ip_addrs.is_empty());
-                ip_addrs.push(wire::IpCidr::Ipv4(ip_cidr)).unwrap();
+                if let Some(ip_cidr) = ip_cidr {
+                    ip_addrs.push(wire::IpCidr::Ipv4(ip_cidr)).unwrap();
+                }
+                ip_addrs
+                    .push(wire::IpCidr::Ipv6(Ipv6Cidr::new(
+                        link_local_addr,
+                        LINK_LOCAL_PREFIX_LEN,
+                    )))
+                    .unwrap();
             });
-            interface
-                .routes_mut()
-                .add_default_ipv4_route(gateway)
-                .unwrap();
-            interface
+            if let Some(gateway) = gateway {
+                interface
+                    .routes_mut()
+                    .add_default_ipv4_route(gateway)
+                    .unwrap();
+            }
+            (interface, device.capabilities().ip_mtu())
         });
 
-        let common = IfaceCommon::new(name, interface, sched_poll);
+        let flags = InterfaceFlags::UP
+            | InterfaceFlags::BROADCAST
+            | InterfaceFlags::RUNNING
+            | InterfaceFlags::MULTICAST
+            | InterfaceFlags::LOWER_UP;
+        let common = IfaceCommon::new(
+            name,
+            InterfaceType::Ether,
+            flags,
+            mtu,
+            interface,
+            sched_poll,
+        );
 
         Arc::new(Self {
             driver,
             common,
             ether_addr,
+            link_local_addr,
             arp_table: SpinLock::new(BTreeMap::new()),
+            ndp_table: SpinLock::new(BTreeMap::new()),
+            is_router_solicited: AtomicBool::new(false),
+            pending_router_config: SpinLock::new(None),
         })
     }
 }
@@ -76,57 +136,102 @@ where
 {
     fn poll(&self) {
         self.driver.with(|device| {
+            if !self.is_router_solicited.swap(true, Ordering::Relaxed) {
+                self.solicit_router(&mut *device);
+            }
+
             let next_poll = self.common.poll(
                 &mut *device,
                 |data, iface_cx, tx_token| self.process(data, iface_cx, tx_token),
                 |pkt, iface_cx, tx_token| self.dispatch(pkt, iface_cx, tx_token),
             );
             device.notify_poll_end();
+
+            // The interface is locked during polling, so the configuration learned from Router
+            // Advertisement messages can only be applied after polling.
+            if let Some(config) = self.pending_router_config.lock().take() {
+                self.apply_router_config(&config);
+            }
+
             self.common.sched_poll().schedule_next_poll(next_poll);
         });
     }
 }
 
+impl<D: WithDevice, E: Ext> EtherIface<D, E> {
+    /// Sends a Router Solicitation message to discover the routers on the link.
+    ///
+    /// Reference: <https://datatracker.ietf.org/doc/html/rfc4861#section-6.3.7>.
+    fn solicit_router(&self, device: &mut D::Device) {
+        let Some(tx_token) = device.transmit(get_network_timestamp()) else {
+            return;
+        };
+
+        let ip_pkt = new_ndisc_packet(
+            self.link_local_addr,
+            IPV6_ALL_ROUTERS,
+            NdiscRepr::RouterSolicit {
+                lladdr: Some(RawHardwareAddress::from_bytes(self.ether_addr.as_bytes())),
+            },
+        );
+        let ether_repr = EthernetRepr {
+            src_addr: self.ether_addr,
+            dst_addr: ipv6_multicast_ether(&IPV6_ALL_ROUTERS),
+            ethertype: EthernetProtocol::Ipv6,
+        };
+        Self::emit_ip(&ether_repr, &ip_pkt, &device.capabilities(), tx_token);
+    }
+}
+
 impl<D, E: Ext> EtherIface<D, E> {
     fn process<'pkt, T: TxToken>(
         &self,
         data: &'pkt [u8],
         iface_cx: &mut Context,
         tx_token: T,
-    ) -> Option<(Ipv4Packet<&'pkt [u8]>, T)> {
-        match self.parse_ip_or_process_arp(data, iface_cx) {
+    ) -> Option<(IpPacket<&'pkt [u8]>, T)> {
+        match self.parse_ip_or_process_link(data, iface_cx) {
             Ok(pkt) => Some((pkt, tx_token)),
-            Err(Some(arp)) => {
-                Self::emit_arp(&arp, tx_token);
+            Err(Some(link_pkt)) => {
+                self.emit_link(&link_pkt, &iface_cx.caps, tx_token);
                 None
             }
             Err(None) => None,
         }
     }
 
-    fn parse_ip_or_process_arp<'pkt>(
+    fn parse_ip_or_process_link<'pkt>(
         &self,
         data: &'pkt [u8],
         iface_cx: &mut Context,
-    ) -> Result<Ipv4Packet<&'pkt [u8]>, Option<ArpRepr>> {
+    ) -> Result<IpPacket<&'pkt [u8]>, Option<LinkPacket>> {
         // Parse the Ethernet header. Ignore the packet if the header is ill-formed.
         let frame = EthernetFrame::new_checked(data).map_err(|_| None)?;
         let repr = EthernetRepr::parse(&frame).map_err(|_| None)?;
 
-        // Ignore the Ethernet frame if it is not sent to us.
-        if !repr.dst_addr.is_broadcast() && repr.dst_addr != self.ether_addr {
+        // Ignore the Ethernet frame if it is not sent to us. IPv6 multicast frames are accepted
+        // because NDP relies on them.
+        //
+        // TODO: Check whether we have joined the multicast group.
+        let is_ipv6_multicast = is_ipv6_multicast_ether(&repr.dst_addr);
+        if !repr.dst_addr.is_broadcast() && repr.dst_addr != self.ether_addr && !is_ipv6_multicast {
             return Err(None);
         }
 
         // Ignore the Ethernet frame if the protocol is not supported.
         match repr.ethertype {
-            EthernetProtocol::Ipv4 => {
-                Ok(Ipv4Packet::new_checked(frame.payload()).map_err(|_| None)?)
+            EthernetProtocol::Ipv4 if !is_ipv6_multicast => Ok(IpPacket::Ipv4(
+                Ipv4Packet::new_checked(frame.payload()).map_err(|_| None)?,
+            )),
+            EthernetProtocol::Ipv6 => {
+                let pkt = Ipv6Packet::new_checked(frame.payload()).map_err(|_| None)?;
+                self.process_ndisc(&pkt, iface_cx)?;
+                Ok(IpPacket::Ipv6(pkt))
             }
-            EthernetProtocol::Arp => {
+            EthernetProtocol::Arp if !is_ipv6_multicast => {
                 let pkt = ArpPacket::new_checked(frame.payload()).map_err(|_| None)?;
                 let arp = ArpRepr::parse(&pkt).map_err(|_| None)?;
-                Err(self.process_arp(&arp, iface_cx))
+                Err(self.process_arp(&arp, iface_cx).map(LinkPacket::Arp))
             }
             _ => Err(None),
         }
@@ -188,48 +293,285 @@ impl<D, E: Ext> EtherIface<D, E> {
         }
     }
 
+    /// Processes the IPv6 packet if it is a Neighbor Discovery Protocol (NDP) message.
+    ///
+    /// This method returns `Ok(())` if the packet is not an NDP message and should be processed
+    /// by the IP layer.
+    fn process_ndisc(
+        &self,
+        pkt: &Ipv6Packet<&[u8]>,
+        iface_cx: &mut Context,
+    ) -> Result<(), Option<LinkPacket>> {
+        let ipv6_repr = Ipv6Repr::parse(pkt).map_err(|_| None)?;
+        if ipv6_repr.next_header != IpProtocol::Icmpv6 {
+            return Ok(());
+        }
+
+        let Ok(icmp_pkt) = Icmpv6Packet::new_checked(pkt.payload()) else {
+            return Ok(());
+        };
+        let Ok(Icmpv6Repr::Ndisc(ndisc_repr)) = Icmpv6Repr::parse(
+            &ipv6_repr.src_addr,
+            &ipv6_repr.dst_addr,
+            &icmp_pkt,
+            &iface_cx.checksum_caps(),
+        ) else {
+            return Ok(());
+        };
+
+        // Ignore the NDP message if it may have been forwarded by a router.
+        //
+        // Reference: <https://datatracker.ietf.org/doc/html/rfc4861#section-7.1.1>.
+        if ipv6_repr.hop_limit != NDISC_HOP_LIMIT {
+            return Err(None);
+        }
+
+        Err(self.process_ndisc_repr(&ipv6_repr, &ndisc_repr, iface_cx))
+    }
+
+    fn process_ndisc_repr(
+        &self,
+        ipv6_repr: &Ipv6Repr,
+        ndisc_repr: &NdiscRepr,
+        iface_cx: &mut Context,
+    ) -> Option<LinkPacket> {
+        let src_addr = ipv6_repr.src_addr;
+
+        match ndisc_repr {
+            NdiscRepr::NeighborSolicit {
+                target_addr,
+                lladdr,
+            } => {
+                // Ignore the NDP message if we do not own the target address.
+                if !iface_cx.has_ip_addr(*target_addr) {
+                    return None;
+                }
+
+                // A Neighbor Solicitation message from the unspecified address is sent for
+                // duplicate address detection (DAD). We need to reply to all nodes.
+                //
+                // Reference: <https://datatracker.ietf.org/doc/html/rfc4861#section-7.2.4>.
+                let (dst_addr, dst_ether, flags) = if src_addr.is_unspecified() {
+                    (
+                        IPV6_ALL_NODES,
+                        ipv6_multicast_ether(&IPV6_ALL_NODES),
+                        NdiscNeighborFlags::OVERRIDE,
+                    )
+                } else {
+                    let dst_ether = match lladdr.as_ref().and_then(parse_lladdr) {
+                        Some(src_ether) => {
+                            self.ndp_table.lock().insert(src_addr, src_ether);
+                            src_ether
+                        }
+                        None => *self.ndp_table.lock().get(&src_addr)?,
+                    };
+                    (
+                        src_addr,
+                        dst_ether,
+                        NdiscNeighborFlags::SOLICITED | NdiscNeighborFlags::OVERRIDE,
+                    )
+                };
+
+                Some(LinkPacket::Ndisc {
+                    dst_ether,
+                    ip_pkt: new_ndisc_packet(
+                        *target_addr,
+                        dst_addr,
+                        NdiscRepr::NeighborAdvert {
+                            flags,
+                            target_addr: *target_addr,
+                            lladdr: Some(RawHardwareAddress::from_bytes(
+                                self.ether_addr.as_bytes(),
+                            )),
+                        },
+                    ),
+                })
+            }
+            NdiscRepr::NeighborAdvert {
+                target_addr,
+                lladdr: Some(lladdr),
+                ..
+            } => {
+                // Insert the mapping between the Ethernet address and the IP address.
+                //
+                // TODO: Remove the mapping if it expires.
+                if let Some(target_ether) = parse_lladdr(lladdr) {
+                    if !target_addr.is_multicast() {
+                        self.ndp_table.lock().insert(*target_addr, target_ether);
+                    }
+                }
+
+                None
+            }
+            NdiscRepr::RouterAdvert {
+                router_lifetime,
+                lladdr,
+                prefix_info,
+                ..
+            } => {
+                // Ignore the NDP message if the source address is not link-local.
+                //
+                // Reference: <https://datatracker.ietf.org/doc/html/rfc4861#section-6.1.2>.
+                if !src_addr.is_unicast_link_local() {
+                    return None;
+                }
+
+                if let Some(src_ether) = lladdr.as_ref().and_then(parse_lladdr) {
+                    self.ndp_table.lock().insert(src_addr, src_ether);
+                }
+
+                // Generate an address from the prefix if the prefix can be used for SLAAC.
+                //
+                // Reference: <https://datatracker.ietf.org/doc/html/rfc4862#section-5.5.3>.
+                let slaac_cidr = prefix_info.as_ref().and_then(|info| {
+                    if !info.flags.contains(NdiscPrefixInfoFlags::ADDRCONF)
+                        || info.prefix_len != SLAAC_PREFIX_LEN
+                        || info.prefix.is_unicast_link_local()
+                        || info.valid_lifetime == Duration::ZERO
+                    {
+                        return None;
+                    }
+                    Some(Ipv6Cidr::new(
+                        eui64_addr(&info.prefix, &self.ether_addr),
+                        SLAAC_PREFIX_LEN,
+                    ))
+                });
+
+                // TODO: Remove the default route and the address if they expire.
+                *self.pending_router_config.lock() = Some(RouterConfig {
+                    default_router: (*router_lifetime != Duration::ZERO).then_some(src_addr),
+                    slaac_cidr,
+                });
+
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn apply_router_config(&self, config: &RouterConfig) {
+        // The default route or the address may already exist if the router has advertised them
+        // before. Errors are ignored because there is nothing else we can do.
+        if let Some(router) = config.default_router {
+            let _ = self.common.set_ipv6_default_route(router);
+        }
+        if let Some(slaac_cidr) = config.slaac_cidr {
+            let _ = self.common.add_ipv6_cidr(slaac_cidr);
+        }
+    }
+
     fn dispatch<T: TxToken>(&self, pkt: &Packet, iface_cx: &mut Context, tx_token: T) {
-        match self.resolve_ether_or_generate_arp(pkt, iface_cx) {
+        match self.resolve_ether_or_generate_link(pkt, iface_cx) {
             Ok(ether) => Self::emit_ip(&ether, pkt, &iface_cx.caps, tx_token),
-            Err(Some(arp)) => Self::emit_arp(&arp, tx_token),
+            Err(Some(link_pkt)) => self.emit_link(&link_pkt, &iface_cx.caps, tx_token),
             Err(None) => (),
         }
     }
 
-    fn resolve_ether_or_generate_arp(
+    fn resolve_ether_or_generate_link(
         &self,
         pkt: &Packet,
         iface_cx: &mut Context,
-    ) -> Result<EthernetRepr, Option<ArpRepr>> {
+    ) -> Result<EthernetRepr, Option<LinkPacket>> {
+        let (next_hop_ether, ethertype) = match pkt.ip_repr().dst_addr() {
+            IpAddress::Ipv4(dst_addr) => (
+                self.resolve_ipv4_or_generate_arp(dst_addr, iface_cx)
+                    .map_err(|arp| arp.map(LinkPacket::Arp))?,
+                EthernetProtocol::Ipv4,
+            ),
+            IpAddress::Ipv6(dst_addr) => (
+                self.resolve_ipv6_or_generate_ndisc(dst_addr, iface_cx)?,
+                EthernetProtocol::Ipv6,
+            ),
+        };
+
+        Ok(EthernetRepr {
+            src_addr: self.ether_addr,
+            dst_addr: next_hop_ether,
+            ethertype,
+        })
+    }
+
+    fn resolve_ipv4_or_generate_arp(
+        &self,
+        dst_addr: Ipv4Address,
+        iface_cx: &mut Context,
+    ) -> Result<EthernetAddress, Option<ArpRepr>> {
         // Resolve the next-hop IP address.
-        let next_hop_ip = match iface_cx.route(&pkt.ip_repr().dst_addr(), iface_cx.now()) {
+        let next_hop_ip = match iface_cx.route(&IpAddress::Ipv4(dst_addr), iface_cx.now()) {
             Some(IpAddress::Ipv4(next_hop_ip)) => next_hop_ip,
-            None => return Err(None),
+            _ => return Err(None),
         };
 
         // Resolve the next-hop Ethernet address.
-        let next_hop_ether = if next_hop_ip.is_broadcast() {
-            EthernetAddress::BROADCAST
+        if next_hop_ip.is_broadcast() {
+            Ok(EthernetAddress::BROADCAST)
         } else if let Some(next_hop_ether) = self.arp_table.lock().get(&next_hop_ip) {
-            *next_hop_ether
+            Ok(*next_hop_ether)
         } else {
             // If the next-hop Ethernet address cannot be resolved, we drop the original packet and
             // send an ARP packet instead. The upper layer should be responsible for detecting the
             // packet loss and retrying later to see if the Ethernet address is ready.
-            return Err(Some(ArpRepr::EthernetIpv4 {
+            Err(Some(ArpRepr::EthernetIpv4 {
                 operation: ArpOperation::Request,
                 source_hardware_addr: self.ether_addr,
                 source_protocol_addr: iface_cx.ipv4_addr().unwrap_or(Ipv4Address::UNSPECIFIED),
                 target_hardware_addr: EthernetAddress::BROADCAST,
                 target_protocol_addr: next_hop_ip,
-            }));
+            }))
+        }
+    }
+
+    fn resolve_ipv6_or_generate_ndisc(
+        &self,
+        dst_addr: Ipv6Address,
+        iface_cx: &mut Context,
+    ) -> Result<EthernetAddress, Option<LinkPacket>> {
+        if dst_addr.is_multicast() {
+            return Ok(ipv6_multicast_ether(&dst_addr));
+        }
+
+        // Resolve the next-hop IP address.
+        let next_hop_ip = match iface_cx.route(&IpAddress::Ipv6(dst_addr), iface_cx.now()) {
+            Some(IpAddress::Ipv6(next_hop_ip)) => next_hop_ip,
+            _ => return Err(None),
         };
 
-        Ok(EthernetRepr {
-            src_addr: self.ether_addr,
-            dst_addr: next_hop_ether,
-            ethertype: EthernetProtocol::Ipv4,
-        })
+        // Resolve the next-hop Ethernet address.
+        if let Some(next_hop_ether) = self.ndp_table.lock().get(&next_hop_ip) {
+            return Ok(*next_hop_ether);
+        }
+
+        // Like ARP, we drop the original packet and send a Neighbor Solicitation message instead.
+        //
+        // Reference: <https://datatracker.ietf.org/doc/html/rfc4861#section-7.2.2>.
+        let solicited_node_addr = solicited_node_addr(&next_hop_ip);
+        Err(Some(LinkPacket::Ndisc {
+            dst_ether: ipv6_multicast_ether(&solicited_node_addr),
+            ip_pkt: new_ndisc_packet(
+                self.link_local_addr,
+                solicited_node_addr,
+                NdiscRepr::NeighborSolicit {
+                    target_addr: next_hop_ip,
+                    lladdr: Some(RawHardwareAddress::from_bytes(self.ether_addr.as_bytes())),
+                },
+            ),
+        }))
+    }
+
+    /// Consumes the token and emits a link-layer packet.
+    fn emit_link<T: TxToken>(&self, link_pkt: &LinkPacket, caps: &DeviceCapabilities, tx_token: T) {
+        match link_pkt {
+            LinkPacket::Arp(arp_repr) => Self::emit_arp(arp_repr, tx_token),
+            LinkPacket::Ndisc { dst_ether, ip_pkt } => {
+                let ether_repr = EthernetRepr {
+                    src_addr: self.ether_addr,
+                    dst_addr: *dst_ether,
+                    ethertype: EthernetProtocol::Ipv6,
+                };
+                Self::emit_ip(&ether_repr, ip_pkt, caps, tx_token)
+            }
+        }
     }
 
     /// Consumes the token and emits an IP packet.
@@ -280,3 +622,99 @@ impl<D, E: Ext> EtherIface<D, E> {
         });
     }
 }
+
+/// Creates an IPv6 packet that carries the NDP message.
+fn new_ndisc_packet(
+    src_addr: Ipv6Address,
+    dst_addr: Ipv6Address,
+    ndisc_repr: NdiscRepr<'static>,
+) -> Packet<'static> {
+    let icmp_repr = Icmpv6Repr::Ndisc(ndisc_repr);
+    Packet::new_ipv6(
+        Ipv6Repr {
+            src_addr,
+            dst_addr,
+            next_header: IpProtocol::Icmpv6,
+            payload_len: icmp_repr.buffer_len(),
+            hop_limit: NDISC_HOP_LIMIT,
+        },
+        IpPayload::Icmpv6(icmp_repr),
+    )
+}
+
+/// Parses the link-layer address option of an NDP message.
+fn parse_lladdr(lladdr: &RawHardwareAddress) -> Option<EthernetAddress> {
+    match lladdr.parse(Medium::Ethernet) {
+        Ok(HardwareAddress::Ethernet(ether_addr)) if ether_addr.is_unicast() => Some(ether_addr),
+        _ => None,
+    }
+}
+
+/// Generates an IPv6 address from the 64-bit prefix and the modified EUI-64 interface
+/// identifier derived from the Ethernet address.
+///
+/// Reference: <https://datatracker.ietf.org/doc/html/rfc4291#appendix-A>.
+fn eui64_addr(prefix: &Ipv6Address, ether_addr: &EthernetAddress) -> Ipv6Address {
+    let mac = ether_addr.0;
+    let mut octets = prefix.octets();
+    octets[8..].copy_from_slice(&[
+        mac[0] ^ 0x02,
+        mac[1],
+        mac[2],
+        0xff,
+        0xfe,
+        mac[3],
+        mac[4],
+        mac[5],
+    ]);
+    Ipv6Address::from(octets)
+}
+
+/// Returns the solicited-node multicast address of the IPv6 address.
+///
+/// Reference: <https://datatracker.ietf.org/doc/html/rfc4291#section-2.7.1>.
+fn solicited_node_addr(addr: &Ipv6Address) -> Ipv6Address {
+    let octets = addr.octets();
+    Ipv6Address::new(
+        0xff02,
+        0,
+        0,
+        0,
+        0,
+        1,
+        0xff00 | octets[13] as u16,
+        u16::from_be_bytes([octets[14], octets[15]]),
+    )
+}
+
+/// Returns the Ethernet address that the IPv6 multicast address maps to.
+///
+/// Reference: <https://datatracker.ietf.org/doc/html/rfc2464#section-7>.
+fn ipv6_multicast_ether(addr: &Ipv6Address) -> EthernetAddress {
+    let octets = addr.octets();
+    EthernetAddress([0x33, 0x33, octets[12], octets[13], octets[14], octets[15]])
+}
+
+/// Returns whether the Ethernet address is mapped from an IPv6 multicast address.
+fn is_ipv6_multicast_ether(ether_addr: &EthernetAddress) -> bool {
+    ether_addr.0[0] == 0x33 && ether_addr.0[1] == 0x33
+}
+
+/// The prefix of the link-local addresses (`fe80::/64`).
+const LINK_LOCAL_PREFIX: Ipv6Address = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 0);
+const LINK_LOCAL_PREFIX_LEN: u8 = 64;
+
+/// The length of the prefixes that can be used for SLAAC on Ethernet links.
+///
+/// Reference: <https://datatracker.ietf.org/doc/html/rfc2464#section-4>.
+const SLAAC_PREFIX_LEN: u8 = 64;
+
+/// The all-nodes multicast address (`ff02::1`).
+const IPV6_ALL_NODES: Ipv6Address = Ipv6Address::new(0xff02, 0, 0, 0, 0, 0, 0, 1);
+/// The all-routers multicast address (`ff02::2`).
+const IPV6_ALL_ROUTERS: Ipv6Address = Ipv6Address::new(0xff02, 0, 0, 0, 0, 0, 0, 2);
+
+/// The hop limit of NDP messages.
+///
+/// Reference: <https://datatracker.ietf.org/doc/html/rfc4861#section-4>.
+const NDISC_HOP_LIMIT: u8 = 255;
@@ -7,18 +7,23 @@ use smoltcp::{
         packet::{icmp_reply_payload_len, IpPayload, Packet},
         Context,
     },
-    phy::{ChecksumCapabilities, Device, RxToken, TxToken},
+    phy::{ChecksumCapabilities, Device, Medium, RxToken, TxToken},
     wire::{
-        Icmpv4DstUnreachable, Icmpv4Repr, IpAddress, IpProtocol, IpRepr, Ipv4Address, Ipv4Packet,
-        Ipv4Repr, TcpControl, TcpPacket, TcpRepr, UdpPacket, UdpRepr, IPV4_HEADER_LEN,
-        IPV4_MIN_MTU,
+        Icmpv4DstUnreachable, Icmpv4Packet, Icmpv4Repr, Icmpv6DstUnreachable, Icmpv6Packet,
+        Icmpv6Repr, IpAddress, IpProtocol, IpRepr, IpVersion, Ipv4Address, Ipv4Packet, Ipv4Repr,
+        Ipv6Packet, Ipv6Repr, TcpControl, TcpPacket, TcpRepr, UdpPacket, UdpRepr, IPV4_HEADER_LEN,
+        IPV4_MIN_MTU, IPV6_HEADER_LEN, IPV6_MIN_MTU,
     },
 };
 
-use super::poll_iface::PollableIfaceMut;
+use super::{
+    capture::{capture, CaptureDevice, CaptureTxToken},
+    poll_iface::PollableIfaceMut,
+};
 use crate::{
     ext::Ext,
-    socket::{TcpConnectionBg, TcpProcessResult},
+    filter::{is_accepted, is_local_accepted, FilterHook, PacketInfo},
+    socket::{FrameDirection, TcpConnectionBg, TcpProcessResult},
     socket_table::{ConnectionKey, ListenerKey, SocketTable},
 };
 
@@ -51,6 +56,24 @@ impl<'a, E: Ext> PollContext<'a, E> {
     }
 }
 
+/// An IP packet, which is either an IPv4 packet or an IPv6 packet.
+pub(super) enum IpPacket<T: AsRef<[u8]>> {
+    Ipv4(Ipv4Packet<T>),
+    Ipv6(Ipv6Packet<T>),
+}
+
+impl<'pkt> IpPacket<&'pkt [u8]> {
+    /// Parses an IP packet whose version is indicated by its first byte.
+    ///
+    /// This method returns `None` if the packet is ill-formed.
+    pub(super) fn new_checked(data: &'pkt [u8]) -> Option<Self> {
+        match IpVersion::of_packet(data).ok()? {
+            IpVersion::Ipv4 => Some(Self::Ipv4(Ipv4Packet::new_checked(data).ok()?)),
+            IpVersion::Ipv6 => Some(Self::Ipv6(Ipv6Packet::new_checked(data).ok()?)),
+        }
+    }
+}
+
 // This works around <https://github.com/rust-lang/rust/issues/49601>.
 // See the issue above for details.
 pub(super) trait FnHelper<A, B, C, O>: FnMut(A, B, C) -> O {}
@@ -59,7 +82,7 @@ impl<A, B, C, O, F> FnHelper<A, B, C, O> for F where F: FnMut(A, B, C) -> O {}
 impl<E: Ext> PollContext<'_, E> {
     pub(super) fn poll_ingress<D, P, Q>(
         &mut self,
-        device: &mut D,
+        device: &mut CaptureDevice<'_, D, E>,
         process_phy: &mut P,
         dispatch_phy: &mut Q,
     ) where
@@ -67,10 +90,13 @@ impl<E: Ext> PollContext<'_, E> {
         P: for<'pkt, 'cx, 'tx> FnHelper<
             &'pkt [u8],
             &'cx mut Context,
-            D::TxToken<'tx>,
-            Option<(Ipv4Packet<&'pkt [u8]>, D::TxToken<'tx>)>,
+            CaptureTxToken<'tx, D::TxToken<'tx>, E>,
+            Option<(
+                IpPacket<&'pkt [u8]>,
+                CaptureTxToken<'tx, D::TxToken<'tx>, E>,
+            )>,
         >,
-        Q: FnMut(&Packet, &mut Context, D::TxToken<'_>),
+        Q: FnMut(&Packet, &mut Context, CaptureTxToken<'_, D::TxToken<'_>, E>),
     {
         while let Some((rx_token, tx_token)) = device.receive(self.iface.context().now()) {
             rx_token.consume(|data| {
@@ -79,7 +105,11 @@ impl<E: Ext> PollContext<'_, E> {
                     return;
                 };
 
-                let Some(reply) = self.parse_and_process_ipv4(pkt) else {
+                let reply = match pkt {
+                    IpPacket::Ipv4(pkt) => self.parse_and_process_ipv4(pkt),
+                    IpPacket::Ipv6(pkt) => self.parse_and_process_ipv6(pkt),
+                };
+                let Some(reply) = reply else {
                     return;
                 };
 
@@ -95,14 +125,29 @@ impl<E: Ext> PollContext<'_, E> {
         // Parse the IP header. Ignore the packet if the header is ill-formed.
         let repr = Ipv4Repr::parse(&pkt, &self.iface.context().checksum_caps()).ok()?;
 
+        let info = PacketInfo::new(&IpRepr::Ipv4(repr), pkt.payload());
+        if !is_accepted::<E::PacketFilter>(FilterHook::Prerouting, &info) {
+            return None;
+        }
+
         if !repr.dst_addr.is_broadcast() && !self.is_unicast_local(IpAddress::Ipv4(repr.dst_addr)) {
             return self.generate_icmp_unreachable(
                 &IpRepr::Ipv4(repr),
                 pkt.payload(),
-                Icmpv4DstUnreachable::HostUnreachable,
+                DstUnreachable::Host,
             );
         }
 
+        if !is_accepted::<E::PacketFilter>(FilterHook::Input, &info) {
+            return None;
+        }
+
+        self.process_raw(
+            IpVersion::Ipv4,
+            repr.next_header,
+            &pkt.as_ref()[..pkt.total_len() as usize],
+        );
+
         let checksum_caps = self.iface.context().checksum_caps();
         match repr.next_header {
             IpProtocol::Tcp => {
@@ -111,6 +156,139 @@ impl<E: Ext> PollContext<'_, E> {
             IpProtocol::Udp => {
                 self.parse_and_process_udp(&IpRepr::Ipv4(repr), pkt.payload(), &checksum_caps)
             }
+            IpProtocol::Icmp => self.parse_and_process_icmpv4(&repr, pkt.payload(), &checksum_caps),
+            _ => None,
+        }
+    }
+
+    fn parse_and_process_ipv6<'pkt>(
+        &mut self,
+        pkt: Ipv6Packet<&'pkt [u8]>,
+    ) -> Option<Packet<'pkt>> {
+        // Parse the IP header. Ignore the packet if the header is ill-formed.
+        let repr = Ipv6Repr::parse(&pkt).ok()?;
+
+        let info = PacketInfo::new(&IpRepr::Ipv6(repr), pkt.payload());
+        if !is_accepted::<E::PacketFilter>(FilterHook::Prerouting, &info) {
+            return None;
+        }
+
+        // Ignore the packet if it is not sent to us. Unlike IPv4, no ICMP messages are generated
+        // here, since we need one of the local addresses as the source address of the ICMP
+        // message, but the destination address of the packet is not local.
+        //
+        // TODO: Check whether we have joined the multicast group.
+        if !repr.dst_addr.is_multicast() && !self.is_unicast_local(IpAddress::Ipv6(repr.dst_addr)) {
+            return None;
+        }
+
+        if !is_accepted::<E::PacketFilter>(FilterHook::Input, &info) {
+            return None;
+        }
+
+        self.process_raw(
+            IpVersion::Ipv6,
+            repr.next_header,
+            &pkt.as_ref()[..pkt.total_len()],
+        );
+
+        // TODO: Support IPv6 extension headers.
+        let checksum_caps = self.iface.context().checksum_caps();
+        match repr.next_header {
+            IpProtocol::Tcp => {
+                self.parse_and_process_tcp(&IpRepr::Ipv6(repr), pkt.payload(), &checksum_caps)
+            }
+            IpProtocol::Udp => {
+                self.parse_and_process_udp(&IpRepr::Ipv6(repr), pkt.payload(), &checksum_caps)
+            }
+            IpProtocol::Icmpv6 => {
+                self.parse_and_process_icmpv6(&repr, pkt.payload(), &checksum_caps)
+            }
+            _ => None,
+        }
+    }
+
+    fn parse_and_process_icmpv4<'pkt>(
+        &mut self,
+        ipv4_repr: &Ipv4Repr,
+        ip_payload: &'pkt [u8],
+        checksum_caps: &ChecksumCapabilities,
+    ) -> Option<Packet<'pkt>> {
+        // Parse the ICMP header. Ignore the packet if the header is ill-formed.
+        let icmp_pkt = Icmpv4Packet::new_checked(ip_payload).ok()?;
+        let icmp_repr = Icmpv4Repr::parse(&icmp_pkt, checksum_caps).ok()?;
+
+        match icmp_repr {
+            // Like Linux, echo requests sent to broadcast addresses are ignored (see
+            // `/proc/sys/net/ipv4/icmp_echo_ignore_broadcasts`).
+            Icmpv4Repr::EchoRequest {
+                ident,
+                seq_no,
+                data,
+            } if self.is_unicast_local(IpAddress::Ipv4(ipv4_repr.dst_addr)) => {
+                let reply_repr = Icmpv4Repr::EchoReply {
+                    ident,
+                    seq_no,
+                    data,
+                };
+                Some(Packet::new_ipv4(
+                    Ipv4Repr {
+                        src_addr: ipv4_repr.dst_addr,
+                        dst_addr: ipv4_repr.src_addr,
+                        next_header: IpProtocol::Icmp,
+                        payload_len: reply_repr.buffer_len(),
+                        hop_limit: DEFAULT_HOP_LIMIT,
+                    },
+                    IpPayload::Icmpv4(reply_repr),
+                ))
+            }
+            // Other messages are only delivered to raw sockets.
+            _ => None,
+        }
+    }
+
+    fn parse_and_process_icmpv6<'pkt>(
+        &mut self,
+        ipv6_repr: &Ipv6Repr,
+        ip_payload: &'pkt [u8],
+        checksum_caps: &ChecksumCapabilities,
+    ) -> Option<Packet<'pkt>> {
+        // Parse the ICMPv6 header. Ignore the packet if the header is ill-formed.
+        let icmp_pkt = Icmpv6Packet::new_checked(ip_payload).ok()?;
+        let icmp_repr = Icmpv6Repr::parse(
+            &ipv6_repr.src_addr,
+            &ipv6_repr.dst_addr,
+            &icmp_pkt,
+            checksum_caps,
+        )
+        .ok()?;
+
+        match icmp_repr {
+            // TODO: Reply to echo requests sent to multicast addresses. This requires selecting
+            // one of the local addresses as the source address.
+            Icmpv6Repr::EchoRequest {
+                ident,
+                seq_no,
+                data,
+            } if self.is_unicast_local(IpAddress::Ipv6(ipv6_repr.dst_addr)) => {
+                let reply_repr = Icmpv6Repr::EchoReply {
+                    ident,
+                    seq_no,
+                    data,
+                };
+                Some(Packet::new_ipv6(
+                    Ipv6Repr {
+                        src_addr: ipv6_repr.dst_addr,
+                        dst_addr: ipv6_repr.src_addr,
+                        next_header: IpProtocol::Icmpv6,
+                        payload_len: reply_repr.buffer_len(),
+                        hop_limit: DEFAULT_HOP_LIMIT,
+                    },
+                    IpPayload::Icmpv6(reply_repr),
+                ))
+            }
+            // Neighbor Discovery Protocol (NDP) messages are processed by the link layer (see
+            // `EtherIface`). Other messages are only delivered to raw sockets.
             _ => None,
         }
     }
@@ -154,6 +332,11 @@ impl<E: Ext> PollContext<'_, E> {
                 return Some((ip_repr, tcp_repr));
             }
 
+            let info = PacketInfo::with_ports(&ip_repr, tcp_repr.src_port, tcp_repr.dst_port);
+            if !is_local_accepted::<E::PacketFilter>(&info) {
+                return None;
+            }
+
             let (new_ip_repr, new_tcp_repr) = self.process_tcp(&ip_repr, &tcp_repr)?;
             ip_repr = new_ip_repr;
             tcp_repr = new_tcp_repr;
@@ -265,11 +448,7 @@ impl<E: Ext> PollContext<'_, E> {
         .ok()?;
 
         if !self.process_udp(ip_repr, &udp_repr, udp_pkt.payload()) {
-            return self.generate_icmp_unreachable(
-                ip_repr,
-                ip_payload,
-                Icmpv4DstUnreachable::PortUnreachable,
-            );
+            return self.generate_icmp_unreachable(ip_repr, ip_payload, DstUnreachable::Port);
         }
 
         None
@@ -292,11 +471,86 @@ impl<E: Ext> PollContext<'_, E> {
         processed
     }
 
+    /// Delivers a copy of the IP packet to raw IP sockets.
+    fn process_raw(&self, ip_version: IpVersion, protocol: IpProtocol, packet: &[u8]) {
+        for socket in self.sockets.raw_socket_iter() {
+            socket.process(ip_version, protocol, packet);
+        }
+    }
+
+    /// Processes an outgoing IP packet whose destination is local, as if the packet were received
+    /// from the network.
+    ///
+    /// Replies to the packet are processed in the same way until a reply is sent to a non-local
+    /// address, in which case the reply is dispatched to the link layer.
+    fn process_local<T, Q>(&mut self, pkt: &Packet, tx_token: &mut Option<T>, dispatch_phy: &mut Q)
+    where
+        T: TxToken,
+        Q: FnMut(&Packet, &mut Context, T),
+    {
+        if !is_accepted::<E::PacketFilter>(FilterHook::Output, &PacketInfo::from_packet(pkt)) {
+            return;
+        }
+
+        let mut data = emit_ip_packet(pkt, self.iface.context());
+
+        loop {
+            self.capture_local(&data);
+
+            let reply = match IpPacket::new_checked(&data) {
+                Some(IpPacket::Ipv4(pkt)) => self.parse_and_process_ipv4(pkt),
+                Some(IpPacket::Ipv6(pkt)) => self.parse_and_process_ipv6(pkt),
+                None => None,
+            };
+            let Some(reply) = reply else {
+                return;
+            };
+
+            if !self.is_unicast_local(reply.ip_repr().dst_addr()) {
+                dispatch_phy(&reply, self.iface.context_mut(), tx_token.take().unwrap());
+                return;
+            }
+
+            if !is_accepted::<E::PacketFilter>(FilterHook::Output, &PacketInfo::from_packet(&reply))
+            {
+                return;
+            }
+
+            data = emit_ip_packet(&reply, self.iface.context());
+        }
+    }
+
+    /// Delivers copies of the IP packet that is processed locally to link-layer sockets.
+    ///
+    /// For IP ifaces (e.g., the loopback iface), the packet is captured as if it were transmitted
+    /// and then received by the device. For other ifaces, the packet never reaches the device, so
+    /// it is not captured.
+    fn capture_local(&self, data: &[u8]) {
+        if self.iface.context().caps.medium != Medium::Ip {
+            return;
+        }
+
+        capture(
+            self.sockets,
+            Medium::Ip,
+            data,
+            FrameDirection::Outgoing,
+            None,
+        );
+        capture(
+            self.sockets,
+            Medium::Ip,
+            data,
+            FrameDirection::Incoming,
+            None,
+        );
+    }
+
     fn generate_icmp_unreachable<'pkt>(
         &self,
         ip_repr: &IpRepr,
         ip_payload: &'pkt [u8],
-        reason: Icmpv4DstUnreachable,
+        reason: DstUnreachable,
     ) -> Option<Packet<'pkt>> {
         if !ip_repr.src_addr().is_unicast() || !ip_repr.dst_addr().is_unicast() {
             return None;
@@ -312,29 +566,64 @@ impl<E: Ext> PollContext<'_, E> {
             return None;
         }
 
-        let IpRepr::Ipv4(ipv4_repr) = ip_repr;
+        match ip_repr {
+            IpRepr::Ipv4(ipv4_repr) => {
+                let reply_len =
+                    icmp_reply_payload_len(ip_payload.len(), IPV4_MIN_MTU, IPV4_HEADER_LEN);
+                let icmp_repr = Icmpv4Repr::DstUnreachable {
+                    reason: match reason {
+                        DstUnreachable::Host => Icmpv4DstUnreachable::HostUnreachable,
+                        DstUnreachable::Port => Icmpv4DstUnreachable::PortUnreachable,
+                    },
+                    header: *ipv4_repr,
+                    data: &ip_payload[..reply_len],
+                };
 
-        let reply_len = icmp_reply_payload_len(ip_payload.len(), IPV4_MIN_MTU, IPV4_HEADER_LEN);
-        let icmp_repr = Icmpv4Repr::DstUnreachable {
-            reason,
-            header: *ipv4_repr,
-            data: &ip_payload[..reply_len],
-        };
+                Some(Packet::new_ipv4(
+                    Ipv4Repr {
+                        src_addr: self
+                            .iface
+                            .context()
+                            .ipv4_addr()
+                            .unwrap_or(Ipv4Address::UNSPECIFIED),
+                        dst_addr: ipv4_repr.src_addr,
+                        next_header: IpProtocol::Icmp,
+                        payload_len: icmp_repr.buffer_len(),
+                        hop_limit: DEFAULT_HOP_LIMIT,
+                    },
+                    IpPayload::Icmpv4(icmp_repr),
+                ))
+            }
+            IpRepr::Ipv6(ipv6_repr) => {
+                // The destination address of the original packet will be the source address of
+                // the ICMP message, so it must be one of the local addresses.
+                if !self.is_unicast_local(IpAddress::Ipv6(ipv6_repr.dst_addr)) {
+                    return None;
+                }
+
+                let reply_len =
+                    icmp_reply_payload_len(ip_payload.len(), IPV6_MIN_MTU, IPV6_HEADER_LEN);
+                let icmp_repr = Icmpv6Repr::DstUnreachable {
+                    reason: match reason {
+                        DstUnreachable::Host => Icmpv6DstUnreachable::AddrUnreachable,
+                        DstUnreachable::Port => Icmpv6DstUnreachable::PortUnreachable,
+                    },
+                    header: *ipv6_repr,
+                    data: &ip_payload[..reply_len],
+                };
 
-        Some(Packet::new_ipv4(
-            Ipv4Repr {
-                src_addr: self
-                    .iface
-                    .context()
-                    .ipv4_addr()
-                    .unwrap_or(Ipv4Address::UNSPECIFIED),
-                dst_addr: ipv4_repr.src_addr,
-                next_header: IpProtocol::Icmp,
-                payload_len: icmp_repr.buffer_len(),
-                hop_limit: 64,
-            },
-            IpPayload::Icmpv4(icmp_repr),
-        ))
+                Some(Packet::new_ipv6(
+                    Ipv6Repr {
+                        src_addr: ipv6_repr.dst_addr,
+                        dst_addr: ipv6_repr.src_addr,
+                        next_header: IpProtocol::Icmpv6,
+                        payload_len: icmp_repr.buffer_len(),
+                        hop_limit: DEFAULT_HOP_LIMIT,
+                    },
+                    IpPayload::Icmpv6(icmp_repr),
+                ))
+            }
+        }
     }
 
     /// Returns whether the destination address is the unicast address of a local interface.
@@ -348,15 +637,44 @@ impl<E: Ext> PollContext<'_, E> {
                 .context()
                 .ipv4_addr()
                 .is_some_and(|addr| addr == dst_addr),
+            IpAddress::Ipv6(dst_addr) => self.iface.context().has_ip_addr(dst_addr),
         }
     }
 }
 
+/// Emits the IP packet, including the IP header, into a new buffer.
+fn emit_ip_packet(pkt: &Packet, cx: &Context) -> Vec<u8> {
+    let ip_repr = pkt.ip_repr();
+
+    let mut data = vec![0; ip_repr.buffer_len()];
+    ip_repr.emit(&mut data[..], &cx.checksum_caps());
+    pkt.emit_payload(&ip_repr, &mut data[ip_repr.header_len()..], &cx.caps);
+
+    data
+}
+
+/// The reason why the destination is unreachable.
+#[derive(Debug, Clone, Copy)]
+enum DstUnreachable {
+    /// The destination host is unreachable.
+    Host,
+    /// The destination port is unreachable.
+    Port,
+}
+
+/// The default hop limit (or TTL) of the outgoing ICMP messages.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/net/ip.h#L105>.
+const DEFAULT_HOP_LIMIT: u8 = 64;
+
 impl<E: Ext> PollContext<'_, E> {
-    pub(super) fn poll_egress<D, Q>(&mut self, device: &mut D, dispatch_phy: &mut Q)
-    where
+    pub(super) fn poll_egress<D, Q>(
+        &mut self,
+        device: &mut CaptureDevice<'_, D, E>,
+        dispatch_phy: &mut Q,
+    ) where
         D: Device + ?Sized,
-        Q: FnMut(&Packet, &mut Context, D::TxToken<'_>),
+        Q: FnMut(&Packet, &mut Context, CaptureTxToken<'_, D::TxToken<'_>, E>),
     {
         while let Some(tx_token) = device.transmit(self.iface.context().now()) {
             if !self.dispatch_ipv4(tx_token, dispatch_phy) {
@@ -376,9 +694,15 @@ impl<E: Ext> PollContext<'_, E> {
             return did_something_tcp;
         };
 
-        let (did_something_udp, _tx_token) = self.dispatch_udp(tx_token, dispatch_phy);
+        let (did_something_udp, tx_token) = self.dispatch_udp(tx_token, dispatch_phy);
 
-        did_something_tcp || did_something_udp
+        let Some(tx_token) = tx_token else {
+            return did_something_tcp || did_something_udp;
+        };
+
+        let (did_something_raw, _tx_token) = self.dispatch_raw(tx_token, dispatch_phy);
+
+        did_something_tcp || did_something_udp || did_something_raw
     }
 
     fn dispatch_tcp<T, Q>(&mut self, tx_token: T, dispatch_phy: &mut Q) -> (bool, Option<T>)
@@ -413,6 +737,12 @@ impl<E: Ext> PollContext<'_, E> {
                         return None;
                     }
 
+                    let info =
+                        PacketInfo::with_ports(ip_repr, tcp_repr.src_port, tcp_repr.dst_port);
+                    if !is_local_accepted::<E::PacketFilter>(&info) {
+                        return None;
+                    }
+
                     if !socket.can_process(tcp_repr.dst_port) {
                         return this.process_tcp(ip_repr, tcp_repr);
                     }
@@ -457,14 +787,18 @@ impl<E: Ext> PollContext<'_, E> {
                     );
                 }
                 (None, Some((ip_repr, tcp_repr))) => {
-                    if let Some((new_ip_repr, new_tcp_repr)) =
-                        self.process_tcp_until_outgoing(&ip_repr, &tcp_repr)
-                    {
-                        dispatch_phy(
-                            &Packet::new(new_ip_repr, IpPayload::Tcp(new_tcp_repr)),
-                            self.iface.context_mut(),
-                            tx_token.take().unwrap(),
-                        );
+                    let info =
+                        PacketInfo::with_ports(&ip_repr, tcp_repr.src_port, tcp_repr.dst_port);
+                    if is_local_accepted::<E::PacketFilter>(&info) {
+                        if let Some((new_ip_repr, new_tcp_repr)) =
+                            self.process_tcp_until_outgoing(&ip_repr, &tcp_repr)
+                        {
+                            dispatch_phy(
+                                &Packet::new(new_ip_repr, IpPayload::Tcp(new_tcp_repr)),
+                                self.iface.context_mut(),
+                                tx_token.take().unwrap(),
+                            );
+                        }
                     }
                 }
                 (Some(_), Some(_)) => unreachable!(),
@@ -515,6 +849,11 @@ impl<E: Ext> PollContext<'_, E> {
                     }
                 }
 
+                let info = PacketInfo::with_ports(ip_repr, udp_repr.src_port, udp_repr.dst_port);
+                if !is_local_accepted::<E::PacketFilter>(&info) {
+                    return;
+                }
+
                 if !socket.can_process(udp_repr.dst_port) {
                     // TODO: Generate the ICMP message here once we're able to handle incoming ICMP
                     // messages.
@@ -560,4 +899,38 @@ impl<E: Ext> PollContext<'_, E> {
 
         (did_something, tx_token)
     }
+
+    fn dispatch_raw<T, Q>(&mut self, tx_token: T, dispatch_phy: &mut Q) -> (bool, Option<T>)
+    where
+        T: TxToken,
+        Q: FnMut(&Packet, &mut Context, T),
+    {
+        let mut tx_token = Some(tx_token);
+        let mut did_something = false;
+
+        for socket in self.sockets.raw_socket_iter() {
+            if !socket.need_dispatch() {
+                continue;
+            }
+
+            did_something = true;
+
+            socket.dispatch(|ip_repr, payload| {
+                let pkt = Packet::new(ip_repr.clone(), IpPayload::Raw(payload));
+
+                if !self.is_unicast_local(ip_repr.dst_addr()) {
+                    dispatch_phy(&pkt, self.iface.context_mut(), tx_token.take().unwrap());
+                    return;
+                }
+
+                self.process_local(&pkt, &mut tx_token, dispatch_phy);
+            });
+
+            if tx_token.is_none() {
+                break;
+            }
+        }
+
+        (did_something, tx_token)
+    }
 }
@@ -1,12 +1,19 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use alloc::{collections::btree_set::BTreeSet, sync::Arc};
+use alloc::{collections::btree_set::BTreeSet, sync::Arc, vec::Vec};
 use core::{
     borrow::Borrow,
     sync::atomic::{AtomicU64, Ordering},
 };
 
+use smoltcp::{
+    iface::Route,
+    wire::{HardwareAddress, IpAddress, IpCidr, Ipv4Address, Ipv4Cidr, Ipv6Address, Ipv6Cidr},
+};
+
+use super::IfaceRoute;
 use crate::{
+    errors::ConfigError,
     ext::Ext,
     socket::{NeedIfacePoll, TcpConnectionBg},
 };
@@ -35,10 +42,169 @@ impl<E: Ext> PollableIface<E> {
         }
     }
 
-    pub(super) fn ipv4_addr(&self) -> Option<smoltcp::wire::Ipv4Address> {
+    pub(super) fn ipv4_addr(&self) -> Option<Ipv4Address> {
         self.interface.ipv4_addr()
     }
 
+    pub(super) fn hardware_addr(&self) -> HardwareAddress {
+        self.interface.hardware_addr()
+    }
+
+    pub(super) fn ipv4_cidrs(&self) -> Vec<Ipv4Cidr> {
+        self.interface
+            .ip_addrs()
+            .iter()
+            .filter_map(|ip_cidr| match ip_cidr {
+                IpCidr::Ipv4(ipv4_cidr) => Some(*ipv4_cidr),
+                IpCidr::Ipv6(_) => None,
+            })
+            .collect()
+    }
+
+    pub(super) fn ipv6_cidrs(&self) -> Vec<Ipv6Cidr> {
+        self.interface
+            .ip_addrs()
+            .iter()
+            .filter_map(|ip_cidr| match ip_cidr {
+                IpCidr::Ipv4(_) => None,
+                IpCidr::Ipv6(ipv6_cidr) => Some(*ipv6_cidr),
+            })
+            .collect()
+    }
+
+    pub(super) fn add_ipv4_cidr(&mut self, ipv4_cidr: Ipv4Cidr) -> Result<(), ConfigError> {
+        self.add_ip_cidr(IpCidr::Ipv4(ipv4_cidr))
+    }
+
+    pub(super) fn add_ipv6_cidr(&mut self, ipv6_cidr: Ipv6Cidr) -> Result<(), ConfigError> {
+        self.add_ip_cidr(IpCidr::Ipv6(ipv6_cidr))
+    }
+
+    pub(super) fn remove_ipv4_addr(
+        &mut self,
+        ipv4_addr: Ipv4Address,
+    ) -> Result<Ipv4Cidr, ConfigError> {
+        match self.remove_ip_addr(IpAddress::Ipv4(ipv4_addr))? {
+            IpCidr::Ipv4(ipv4_cidr) => Ok(ipv4_cidr),
+            IpCidr::Ipv6(_) => unreachable!("the removed address must be an IPv4 address"),
+        }
+    }
+
+    pub(super) fn remove_ipv6_addr(
+        &mut self,
+        ipv6_addr: Ipv6Address,
+    ) -> Result<Ipv6Cidr, ConfigError> {
+        match self.remove_ip_addr(IpAddress::Ipv6(ipv6_addr))? {
+            IpCidr::Ipv4(_) => unreachable!("the removed address must be an IPv6 address"),
+            IpCidr::Ipv6(ipv6_cidr) => Ok(ipv6_cidr),
+        }
+    }
+
+    fn add_ip_cidr(&mut self, ip_cidr: IpCidr) -> Result<(), ConfigError> {
+        let mut result = Ok(());
+
+        self.interface.update_ip_addrs(|ip_addrs| {
+            if ip_addrs
+                .iter()
+                .any(|old_cidr| old_cidr.address() == ip_cidr.address())
+            {
+                result = Err(ConfigError::AlreadyExists);
+            } else if ip_addrs.push(ip_cidr).is_err() {
+                result = Err(ConfigError::Exhausted);
+            }
+        });
+
+        result
+    }
+
+    fn remove_ip_addr(&mut self, ip_addr: IpAddress) -> Result<IpCidr, ConfigError> {
+        let mut result = Err(ConfigError::NotFound);
+
+        self.interface.update_ip_addrs(|ip_addrs| {
+            if let Some(index) = ip_addrs
+                .iter()
+                .position(|ip_cidr| ip_cidr.address() == ip_addr)
+            {
+                result = Ok(ip_addrs.remove(index));
+            }
+        });
+
+        result
+    }
+
+    pub(super) fn ipv4_routes(&mut self) -> Vec<IfaceRoute> {
+        let mut routes = Vec::new();
+
+        self.interface.routes_mut().update(|storage| {
+            routes.extend(storage.iter().filter_map(|route| {
+                match (route.cidr, route.via_router) {
+                    (IpCidr::Ipv4(cidr), IpAddress::Ipv4(gateway)) => {
+                        Some(IfaceRoute { cidr, gateway })
+                    }
+                    _ => None,
+                }
+            }));
+        });
+
+        routes
+    }
+
+    pub(super) fn add_ipv4_route(&mut self, route: IfaceRoute) -> Result<(), ConfigError> {
+        self.add_ip_route(IpCidr::Ipv4(route.cidr), IpAddress::Ipv4(route.gateway))
+    }
+
+    pub(super) fn remove_ipv4_route(&mut self, cidr: Ipv4Cidr) -> Result<IfaceRoute, ConfigError> {
+        match self.remove_ip_route(IpCidr::Ipv4(cidr))? {
+            IpAddress::Ipv4(gateway) => Ok(IfaceRoute { cidr, gateway }),
+            IpAddress::Ipv6(_) => unreachable!("the gateway of an IPv4 route must be IPv4"),
+        }
+    }
+
+    /// Adds the default IPv6 route via the router, replacing the old one if it exists.
+    pub(super) fn set_ipv6_default_route(
+        &mut self,
+        router: Ipv6Address,
+    ) -> Result<(), ConfigError> {
+        let cidr = IpCidr::Ipv6(Ipv6Cidr::new(Ipv6Address::UNSPECIFIED, 0));
+        let _ = self.remove_ip_route(cidr);
+        self.add_ip_route(cidr, IpAddress::Ipv6(router))
+    }
+
+    fn add_ip_route(&mut self, cidr: IpCidr, via_router: IpAddress) -> Result<(), ConfigError> {
+        let mut result = Ok(());
+
+        self.interface.routes_mut().update(|storage| {
+            if storage.iter().any(|old_route| old_route.cidr == cidr) {
+                result = Err(ConfigError::AlreadyExists);
+                return;
+            }
+
+            let new_route = Route {
+                cidr,
+                via_router,
+                preferred_until: None,
+                expires_at: None,
+            };
+            if storage.push(new_route).is_err() {
+                result = Err(ConfigError::Exhausted);
+            }
+        });
+
+        result
+    }
+
+    fn remove_ip_route(&mut self, cidr: IpCidr) -> Result<IpAddress, ConfigError> {
+        let mut result = Err(ConfigError::NotFound);
+
+        self.interface.routes_mut().update(|storage| {
+            if let Some(index) = storage.iter().position(|route| route.cidr == cidr) {
+                result = Ok(storage.remove(index).via_router);
+            }
+        });
+
+        result
+    }
+
     /// Returns the next poll time.
     pub(super) fn next_poll_at_ms(&self) -> Option<u64> {
         self.pending_conns.next_poll_at_ms()
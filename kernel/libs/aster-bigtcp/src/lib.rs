@@ -18,6 +18,7 @@ pub mod boolean_value;
 pub mod device;
 pub mod errors;
 pub mod ext;
+pub mod filter;
 pub mod iface;
 pub mod socket;
 pub mod socket_table;
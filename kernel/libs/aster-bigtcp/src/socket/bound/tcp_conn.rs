@@ -24,8 +24,8 @@ use crate::{
     iface::{BoundPort, PollKey, PollableIfaceMut},
     socket::{
         event::SocketEvents,
-        option::{RawTcpOption, RawTcpSetOption},
-        unbound::{new_tcp_socket, RawTcpSocket},
+        option::{RawCongestionControl, RawTcpOption, RawTcpSetOption},
+        unbound::RawTcpSocket,
     },
     socket_table::ConnectionKey,
 };
@@ -293,9 +293,7 @@ impl<E: Ext> TcpConnection<E> {
         option: &RawTcpOption,
         observer: E::TcpEventObserver,
     ) -> Result<Self, (BoundPort<E>, ConnectError)> {
-        let Some(local_endpoint) = bound.endpoint() else {
-            return Err((bound, ConnectError::Unaddressable));
-        };
+        let local_endpoint = bound.endpoint();
 
         let iface = bound.iface().clone();
         // We have to lock `interface` before locking `sockets`
@@ -310,9 +308,7 @@ impl<E: Ext> TcpConnection<E> {
         }
 
         let socket = {
-            let mut socket = new_tcp_socket();
-
-            option.apply(&mut socket);
+            let mut socket = option.new_socket();
 
             if let Err(err) = socket.connect(interface.context_mut(), remote_endpoint, bound.port())
             {
@@ -536,6 +532,11 @@ impl<E: Ext> RawTcpSetOption for TcpConnection<E> {
         let mut socket = self.0.inner.lock();
         socket.set_nagle_enabled(enabled);
     }
+
+    fn set_congestion_control(&self, congestion_control: RawCongestionControl) {
+        let mut socket = self.0.inner.lock();
+        socket.set_congestion_control(congestion_control);
+    }
 }
 
 impl<E: Ext> TcpConnectionBg<E> {
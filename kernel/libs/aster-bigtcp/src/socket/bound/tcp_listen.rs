@@ -18,8 +18,8 @@ use crate::{
     ext::Ext,
     iface::{BindPortConfig, BoundPort, PollableIfaceMut},
     socket::{
-        option::{RawTcpOption, RawTcpSetOption},
-        unbound::{new_tcp_socket, RawTcpSocket},
+        option::{RawCongestionControl, RawTcpOption, RawTcpSetOption},
+        unbound::RawTcpSocket,
     },
     socket_table::{ConnectionKey, ListenerKey},
 };
@@ -72,9 +72,7 @@ impl<E: Ext> TcpListener<E> {
         option: &RawTcpOption,
         observer: E::TcpEventObserver,
     ) -> Result<Self, (BoundPort<E>, ListenError)> {
-        let Some(local_endpoint) = bound.endpoint() else {
-            return Err((bound, ListenError::Unaddressable));
-        };
+        let local_endpoint = bound.endpoint();
 
         let iface = bound.iface().clone();
         let mut sockets = iface.common().sockets();
@@ -86,9 +84,7 @@ impl<E: Ext> TcpListener<E> {
         }
 
         let socket = {
-            let mut socket = new_tcp_socket();
-
-            option.apply(&mut socket);
+            let mut socket = option.new_socket();
 
             if let Err(err) = socket.listen(local_endpoint) {
                 return Err((bound, err.into()));
@@ -181,6 +177,11 @@ impl<E: Ext> RawTcpSetOption for TcpListener<E> {
         let mut backlog = self.0.inner.backlog.lock();
         backlog.socket.set_nagle_enabled(enabled);
     }
+
+    fn set_congestion_control(&self, congestion_control: RawCongestionControl) {
+        let mut backlog = self.0.inner.backlog.lock();
+        backlog.socket.set_congestion_control(congestion_control);
+    }
 }
 
 impl<E: Ext> TcpListenerBg<E> {
@@ -226,8 +227,7 @@ impl<E: Ext> TcpListenerBg<E> {
         }
 
         let new_socket = {
-            let mut socket = new_tcp_socket();
-            RawTcpOption::inherit(&backlog.socket, &mut socket);
+            let mut socket = RawTcpOption::new_inherited_socket(&backlog.socket);
             socket.listen(backlog.socket.listen_endpoint()).unwrap();
             socket
         };
@@ -0,0 +1,228 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::{sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use ostd::sync::{LocalIrqDisabled, SpinLock};
+use smoltcp::wire::{HardwareAddress, ETHERNET_HEADER_LEN};
+use spin::once::Once;
+
+use super::queue::PacketQueue;
+use crate::{
+    errors::link::SendError,
+    ext::Ext,
+    iface::Iface,
+    socket::event::{SocketEventObserver, SocketEvents},
+};
+
+/// A link-layer socket.
+///
+/// A link-layer socket receives a copy of every frame that is received or transmitted by the
+/// iface, as long as the frame matches the [`LinkFilter`] of the socket. It can also transmit
+/// frames that are built by the user of this crate.
+///
+/// The format of the frames depends on the iface. Ethernet ifaces use Ethernet frames, while IP
+/// ifaces (e.g., the loopback iface) use IP packets without any link-layer headers.
+pub struct LinkSocket<E: Ext>(Arc<LinkSocketBg<E>>);
+
+/// The background part of [`LinkSocket`], which captures frames from the iface.
+pub(crate) struct LinkSocketBg<E: Ext> {
+    iface: Arc<dyn Iface<E>>,
+    filter: SpinLock<LinkFilter, LocalIrqDisabled>,
+    recv_queue: SpinLock<PacketQueue<(Vec<u8>, FrameDirection)>, LocalIrqDisabled>,
+    send_queue: SpinLock<PacketQueue<Vec<u8>>, LocalIrqDisabled>,
+    need_dispatch: AtomicBool,
+    observer: Once<E::LinkEventObserver>,
+}
+
+/// The frames that are captured by a [`LinkSocket`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkFilter {
+    /// No frames are captured.
+    None,
+    /// All frames are captured.
+    All,
+    /// Only incoming frames that carry the specified EtherType are captured.
+    ///
+    /// Like Linux, outgoing frames are only captured by sockets that capture all frames. For IP
+    /// ifaces, the EtherType is determined by the IP version of the packet.
+    EtherType(u16),
+}
+
+/// The direction of a frame that is captured by a [`LinkSocket`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameDirection {
+    /// The frame is received by the iface.
+    Incoming,
+    /// The frame is transmitted by the iface.
+    Outgoing,
+}
+
+impl<E: Ext> LinkSocket<E> {
+    /// Creates a link-layer socket on the iface.
+    ///
+    /// Polling the iface is _not_ required after this method succeeds.
+    pub fn new(
+        iface: Arc<dyn Iface<E>>,
+        filter: LinkFilter,
+        observer: E::LinkEventObserver,
+    ) -> Self {
+        let socket = Arc::new(LinkSocketBg {
+            iface,
+            filter: SpinLock::new(filter),
+            recv_queue: SpinLock::new(PacketQueue::new()),
+            send_queue: SpinLock::new(PacketQueue::new()),
+            need_dispatch: AtomicBool::new(false),
+            observer: Once::new(),
+        });
+        socket.observer.call_once(|| observer);
+
+        socket.iface.common().register_link_socket(socket.clone());
+
+        Self(socket)
+    }
+
+    /// Returns a reference to the iface.
+    pub fn iface(&self) -> &Arc<dyn Iface<E>> {
+        &self.0.iface
+    }
+
+    /// Sets the filter that selects the frames to capture.
+    ///
+    /// Frames that have already been captured are _not_ affected.
+    pub fn set_filter(&self, filter: LinkFilter) {
+        *self.0.filter.lock() = filter;
+    }
+
+    /// Returns the length of the link-layer header of the frames.
+    pub fn header_len(&self) -> usize {
+        match self.0.iface.hardware_addr() {
+            HardwareAddress::Ethernet(_) => ETHERNET_HEADER_LEN,
+            _ => 0,
+        }
+    }
+
+    /// Sends a frame, including the link-layer header.
+    ///
+    /// The frame is sent as is, so all headers and checksums must be filled in by the caller.
+    ///
+    /// Polling the iface is _always_ required after this method succeeds.
+    pub fn send(&self, frame: Vec<u8>) -> Result<(), SendError> {
+        if frame.len() < self.header_len() {
+            return Err(SendError::TooShort);
+        }
+        if frame.len() > self.header_len() + self.0.iface.mtu() {
+            return Err(SendError::TooLarge);
+        }
+
+        let len = frame.len();
+        self.0
+            .send_queue
+            .lock()
+            .push(frame, len, LINK_SEND_BUF_LEN)
+            .map_err(|_| SendError::BufferFull)?;
+        self.0.need_dispatch.store(true, Ordering::Relaxed);
+
+        Ok(())
+    }
+
+    /// Receives a frame, including the link-layer header.
+    ///
+    /// Polling the iface is _not_ required after this method succeeds.
+    pub fn recv<F, R>(&self, f: F) -> Option<R>
+    where
+        F: FnOnce(&[u8], FrameDirection) -> R,
+    {
+        let (frame, direction) = self.0.recv_queue.lock().pop(|(frame, _)| frame.len())?;
+        Some(f(&frame, direction))
+    }
+
+    /// Returns whether there are frames that can be received.
+    pub fn can_recv(&self) -> bool {
+        !self.0.recv_queue.lock().is_empty()
+    }
+
+    /// Returns whether more frames can be sent.
+    pub fn can_send(&self) -> bool {
+        self.0.send_queue.lock().total_len() < LINK_SEND_BUF_LEN
+    }
+}
+
+impl<E: Ext> Drop for LinkSocket<E> {
+    fn drop(&mut self) {
+        self.0.iface.common().remove_link_socket(&self.0);
+    }
+}
+
+impl<E: Ext> LinkSocketBg<E> {
+    /// Captures a frame if it matches the filter of the socket.
+    ///
+    /// `ether_type` is the EtherType carried by the frame, if it can be determined.
+    pub(crate) fn capture(&self, frame: &[u8], ether_type: Option<u16>, direction: FrameDirection) {
+        let is_matched = match *self.filter.lock() {
+            LinkFilter::None => false,
+            LinkFilter::All => true,
+            LinkFilter::EtherType(expected) => {
+                direction == FrameDirection::Incoming && ether_type == Some(expected)
+            }
+        };
+        if !is_matched {
+            return;
+        }
+
+        if self
+            .recv_queue
+            .lock()
+            .push((frame.to_vec(), direction), frame.len(), LINK_RECV_BUF_LEN)
+            .is_err()
+        {
+            // The receive queue is full. Linux drops the frame silently.
+            return;
+        }
+
+        self.notify_events(SocketEvents::CAN_RECV);
+    }
+
+    /// Tries to pop an outgoing frame and dispatches the frame.
+    ///
+    /// Like [`RawIpSocketBg::dispatch`], no locks of the socket are held when `dispatch` is
+    /// called.
+    ///
+    /// [`RawIpSocketBg::dispatch`]: super::RawIpSocketBg::dispatch
+    pub(crate) fn dispatch<D>(&self, dispatch: D)
+    where
+        D: FnOnce(&[u8]),
+    {
+        let mut send_queue = self.send_queue.lock();
+        let frame = send_queue.pop(Vec::len);
+        self.need_dispatch
+            .store(!send_queue.is_empty(), Ordering::Relaxed);
+        drop(send_queue);
+
+        let Some(frame) = frame else {
+            return;
+        };
+        dispatch(&frame);
+
+        // Dequeuing a frame means that we can queue more frames.
+        self.notify_events(SocketEvents::CAN_SEND);
+    }
+
+    /// Returns whether the socket _may_ generate an outgoing frame.
+    ///
+    /// The check is intended to be lock-free and fast, but may have false positives.
+    pub(crate) fn need_dispatch(&self) -> bool {
+        self.need_dispatch.load(Ordering::Relaxed)
+    }
+
+    fn notify_events(&self, events: SocketEvents) {
+        if let Some(observer) = self.observer.get() {
+            observer.on_events(events);
+        }
+    }
+}
+
+/// The maximum total size of the frames in the receive queue.
+pub const LINK_RECV_BUF_LEN: usize = 65536;
+/// The maximum total size of the frames in the send queue.
+pub const LINK_SEND_BUF_LEN: usize = 65536;
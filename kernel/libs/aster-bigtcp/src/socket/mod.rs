@@ -2,7 +2,10 @@
 
 mod bound;
 mod event;
+mod link;
 mod option;
+mod queue;
+mod raw;
 mod unbound;
 
 pub use bound::{
@@ -10,7 +13,11 @@ pub use bound::{
 };
 pub(crate) use bound::{TcpConnectionBg, TcpListenerBg, TcpProcessResult, UdpSocketBg};
 pub use event::{SocketEventObserver, SocketEvents};
-pub use option::{RawTcpOption, RawTcpSetOption};
+pub(crate) use link::LinkSocketBg;
+pub use link::{FrameDirection, LinkFilter, LinkSocket, LINK_RECV_BUF_LEN, LINK_SEND_BUF_LEN};
+pub use option::{RawCongestionControl, RawTcpOption, RawTcpSetOption};
+pub(crate) use raw::RawIpSocketBg;
+pub use raw::{RawIpSocket, RAW_RECV_BUF_LEN, RAW_SEND_BUF_LEN};
 pub use unbound::{
     RawUdpSocket, TCP_RECV_BUF_LEN, TCP_SEND_BUF_LEN, UDP_RECV_PAYLOAD_LEN, UDP_SEND_PAYLOAD_LEN,
 };
@@ -1,8 +1,16 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use alloc::boxed::Box;
+
 use smoltcp::time::Duration;
 
-use super::{unbound::RawTcpSocket, NeedIfacePoll};
+use super::{
+    unbound::{new_tcp_socket, RawTcpSocket},
+    NeedIfacePoll, TCP_RECV_BUF_LEN, TCP_SEND_BUF_LEN,
+};
+
+/// The congestion control algorithm of a TCP socket.
+pub type RawCongestionControl = smoltcp::socket::tcp::CongestionControl;
 
 /// A trait defines setting socket options on a raw socket.
 pub trait RawTcpSetOption {
@@ -15,6 +23,11 @@ pub trait RawTcpSetOption {
     ///
     /// Polling the iface is _not_ required after this method succeeds.
     fn set_nagle_enabled(&self, enabled: bool);
+
+    /// Sets the congestion control algorithm.
+    ///
+    /// Polling the iface is _not_ required after this method succeeds.
+    fn set_congestion_control(&self, congestion_control: RawCongestionControl);
 }
 
 /// Socket options on a raw socket.
@@ -23,16 +36,63 @@ pub struct RawTcpOption {
     pub keep_alive: Option<Duration>,
     /// Whether Nagle's algorithm is enabled.
     pub is_nagle_enabled: bool,
+    /// The congestion control algorithm.
+    pub congestion_control: RawCongestionControl,
+    /// The requested size of the receive buffer.
+    pub recv_buf_len: usize,
+    /// The requested size of the send buffer.
+    pub send_buf_len: usize,
 }
 
 impl RawTcpOption {
-    pub(super) fn apply(&self, socket: &mut RawTcpSocket) {
+    /// Creates a new raw socket with the options applied.
+    ///
+    /// The buffer sizes cannot be changed once the raw socket is created. Since smoltcp derives
+    /// the window scale factor from the capacity of the receive buffer, a larger receive buffer
+    /// also means that a larger window can be advertised.
+    ///
+    /// Buffers smaller than the default sizes are not allocated (see [`TCP_RECV_BUF_LEN`] and
+    /// [`TCP_SEND_BUF_LEN`] for the reasons).
+    ///
+    /// There are no options for selective acknowledgments (SACK) or the retransmission timeout
+    /// (RTO), since both are handled inside smoltcp and cannot be configured per socket:
+    ///  - SACK is always offered in SYNs. If the peer agrees, the out-of-order data in the
+    ///    receive buffer is reported in SACK blocks. However, the SACK blocks from the peer are
+    ///    ignored, so lost segments are recovered by fast retransmission and RTO expiration.
+    ///  - The RTO is derived from a smoothed RTT and its variance with the gains in RFC 6298,
+    ///    and the RTT is not sampled from retransmitted segments (Karn's algorithm). Unlike RFC
+    ///    6298, the RTO is bounded to the range from 10 ms to 10 s.
+    ///
+    /// Processing the SACK blocks from the peer or following RFC 6298 more closely requires
+    /// changes to smoltcp itself.
+    pub(super) fn new_socket(&self) -> Box<RawTcpSocket> {
+        let mut socket = new_tcp_socket(
+            self.recv_buf_len.clamp(TCP_RECV_BUF_LEN, MAX_BUF_LEN),
+            self.send_buf_len.clamp(TCP_SEND_BUF_LEN, MAX_BUF_LEN),
+        );
+
         socket.set_keep_alive(self.keep_alive);
         socket.set_nagle_enabled(self.is_nagle_enabled);
+        socket.set_congestion_control(self.congestion_control);
+
+        socket
     }
 
-    pub(super) fn inherit(from: &RawTcpSocket, to: &mut RawTcpSocket) {
-        to.set_keep_alive(from.keep_alive());
-        to.set_nagle_enabled(from.nagle_enabled());
+    /// Creates a new raw socket that inherits the options of an existing raw socket.
+    pub(super) fn new_inherited_socket(from: &RawTcpSocket) -> Box<RawTcpSocket> {
+        let mut socket = new_tcp_socket(from.recv_capacity(), from.send_capacity());
+
+        socket.set_keep_alive(from.keep_alive());
+        socket.set_nagle_enabled(from.nagle_enabled());
+        socket.set_congestion_control(from.congestion_control());
+
+        socket
     }
 }
+
+/// The maximum size of socket buffers.
+///
+/// smoltcp cannot handle receive buffers larger than 1 GiB, because the window scale factor is
+/// limited to 14 (RFC 7323). A much smaller limit is used here to bound the memory consumption of
+/// a single socket.
+const MAX_BUF_LEN: usize = 4 * 1024 * 1024;
@@ -0,0 +1,43 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::collections::vec_deque::VecDeque;
+
+/// A queue of packets whose total length is limited.
+pub(super) struct PacketQueue<T> {
+    packets: VecDeque<T>,
+    total_len: usize,
+}
+
+impl<T> PacketQueue<T> {
+    pub(super) const fn new() -> Self {
+        Self {
+            packets: VecDeque::new(),
+            total_len: 0,
+        }
+    }
+
+    /// Pushes a packet of `len` bytes if the queue has enough space.
+    pub(super) fn push(&mut self, packet: T, len: usize, capacity: usize) -> Result<(), T> {
+        if !self.packets.is_empty() && self.total_len + len > capacity {
+            return Err(packet);
+        }
+
+        self.total_len += len;
+        self.packets.push_back(packet);
+        Ok(())
+    }
+
+    pub(super) fn pop(&mut self, len_of: impl FnOnce(&T) -> usize) -> Option<T> {
+        let packet = self.packets.pop_front()?;
+        self.total_len -= len_of(&packet);
+        Some(packet)
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.packets.is_empty()
+    }
+
+    pub(super) fn total_len(&self) -> usize {
+        self.total_len
+    }
+}
@@ -0,0 +1,196 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::{sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use ostd::sync::{LocalIrqDisabled, SpinLock};
+use smoltcp::wire::{IpProtocol, IpRepr, IpVersion};
+use spin::once::Once;
+
+use super::queue::PacketQueue;
+use crate::{
+    errors::raw::SendError,
+    ext::Ext,
+    iface::Iface,
+    socket::event::{SocketEventObserver, SocketEvents},
+};
+
+/// A raw IP socket.
+///
+/// A raw IP socket is not bound to any ports. Instead, it receives a copy of every incoming IP
+/// packet that carries the specified protocol and is destined to the iface. Outgoing packets are
+/// built by the user of this crate, and only the IP header is generated by the iface.
+pub struct RawIpSocket<E: Ext>(Arc<RawIpSocketBg<E>>);
+
+/// The background part of [`RawIpSocket`], which handles packets from the network.
+pub(crate) struct RawIpSocketBg<E: Ext> {
+    iface: Arc<dyn Iface<E>>,
+    ip_version: IpVersion,
+    protocol: IpProtocol,
+    recv_queue: SpinLock<PacketQueue<Vec<u8>>, LocalIrqDisabled>,
+    send_queue: SpinLock<PacketQueue<(IpRepr, Vec<u8>)>, LocalIrqDisabled>,
+    need_dispatch: AtomicBool,
+    observer: Once<E::RawEventObserver>,
+}
+
+impl<E: Ext> RawIpSocket<E> {
+    /// Creates a raw IP socket on the iface.
+    ///
+    /// Polling the iface is _not_ required after this method succeeds.
+    pub fn new(
+        iface: Arc<dyn Iface<E>>,
+        ip_version: IpVersion,
+        protocol: IpProtocol,
+        observer: E::RawEventObserver,
+    ) -> Self {
+        let socket = Arc::new(RawIpSocketBg {
+            iface,
+            ip_version,
+            protocol,
+            recv_queue: SpinLock::new(PacketQueue::new()),
+            send_queue: SpinLock::new(PacketQueue::new()),
+            need_dispatch: AtomicBool::new(false),
+            observer: Once::new(),
+        });
+        socket.observer.call_once(|| observer);
+
+        socket.iface.common().register_raw_socket(socket.clone());
+
+        Self(socket)
+    }
+
+    /// Returns a reference to the iface.
+    pub fn iface(&self) -> &Arc<dyn Iface<E>> {
+        &self.0.iface
+    }
+
+    /// Sends an IP packet whose header is described by `ip_repr`.
+    ///
+    /// The payload is sent as is, so the checksum of the upper-layer protocol must be filled in by
+    /// the caller.
+    ///
+    /// Polling the iface is _always_ required after this method succeeds.
+    pub fn send(&self, ip_repr: IpRepr, payload: Vec<u8>) -> Result<(), SendError> {
+        if ip_repr.version() != self.0.ip_version {
+            return Err(SendError::Unaddressable);
+        }
+        if ip_repr.header_len() + payload.len() > self.0.iface.mtu() {
+            return Err(SendError::TooLarge);
+        }
+
+        let len = payload.len();
+        self.0
+            .send_queue
+            .lock()
+            .push((ip_repr, payload), len, RAW_SEND_BUF_LEN)
+            .map_err(|_| SendError::BufferFull)?;
+        self.0.need_dispatch.store(true, Ordering::Relaxed);
+
+        Ok(())
+    }
+
+    /// Receives an IP packet, including the IP header.
+    ///
+    /// Polling the iface is _not_ required after this method succeeds.
+    pub fn recv<F, R>(&self, f: F) -> Option<R>
+    where
+        F: FnOnce(&[u8]) -> R,
+    {
+        let packet = self.0.recv_queue.lock().pop(Vec::len)?;
+        Some(f(&packet))
+    }
+
+    /// Returns whether there are packets that can be received.
+    pub fn can_recv(&self) -> bool {
+        !self.0.recv_queue.lock().is_empty()
+    }
+
+    /// Returns whether more packets can be sent.
+    pub fn can_send(&self) -> bool {
+        self.0.send_queue.lock().total_len() < RAW_SEND_BUF_LEN
+    }
+}
+
+impl<E: Ext> Drop for RawIpSocket<E> {
+    fn drop(&mut self) {
+        self.0.iface.common().remove_raw_socket(&self.0);
+    }
+}
+
+impl<E: Ext> RawIpSocketBg<E> {
+    /// Tries to process an incoming packet and returns whether the packet is accepted.
+    ///
+    /// `packet` contains the whole IP packet, including the IP header.
+    pub(crate) fn process(
+        &self,
+        ip_version: IpVersion,
+        protocol: IpProtocol,
+        packet: &[u8],
+    ) -> bool {
+        // Like Linux, `IPPROTO_RAW` sockets are send-only.
+        if ip_version != self.ip_version
+            || protocol != self.protocol
+            || u8::from(self.protocol) == IPPROTO_RAW
+        {
+            return false;
+        }
+
+        if self
+            .recv_queue
+            .lock()
+            .push(packet.to_vec(), packet.len(), RAW_RECV_BUF_LEN)
+            .is_err()
+        {
+            // The receive queue is full. Linux drops the packet silently.
+            return false;
+        }
+
+        self.notify_events(SocketEvents::CAN_RECV);
+
+        true
+    }
+
+    /// Tries to pop an outgoing packet and dispatches the packet.
+    ///
+    /// Unlike other sockets, no locks are held when `dispatch` is called, so it is fine to process
+    /// the packet immediately in `dispatch` even if the packet is sent to this socket itself.
+    pub(crate) fn dispatch<D>(&self, dispatch: D)
+    where
+        D: FnOnce(&IpRepr, &[u8]),
+    {
+        let mut send_queue = self.send_queue.lock();
+        let packet = send_queue.pop(|(_, payload)| payload.len());
+        self.need_dispatch
+            .store(!send_queue.is_empty(), Ordering::Relaxed);
+        drop(send_queue);
+
+        let Some((ip_repr, payload)) = packet else {
+            return;
+        };
+        dispatch(&ip_repr, &payload);
+
+        // Dequeuing a packet means that we can queue more packets.
+        self.notify_events(SocketEvents::CAN_SEND);
+    }
+
+    /// Returns whether the socket _may_ generate an outgoing packet.
+    ///
+    /// The check is intended to be lock-free and fast, but may have false positives.
+    pub(crate) fn need_dispatch(&self) -> bool {
+        self.need_dispatch.load(Ordering::Relaxed)
+    }
+
+    fn notify_events(&self, events: SocketEvents) {
+        if let Some(observer) = self.observer.get() {
+            observer.on_events(events);
+        }
+    }
+}
+
+/// The protocol number of `IPPROTO_RAW`.
+const IPPROTO_RAW: u8 = 255;
+
+/// The maximum total size of the packets in the receive queue.
+pub const RAW_RECV_BUF_LEN: usize = 65536;
+/// The maximum total size of the packets in the send queue.
+pub const RAW_SEND_BUF_LEN: usize = 65536;
@@ -5,10 +5,10 @@ use alloc::{boxed::Box, vec};
 pub(super) type RawTcpSocket = smoltcp::socket::tcp::Socket<'static>;
 pub type RawUdpSocket = smoltcp::socket::udp::Socket<'static>;
 
-pub(super) fn new_tcp_socket() -> Box<RawTcpSocket> {
+pub(super) fn new_tcp_socket(recv_buf_len: usize, send_buf_len: usize) -> Box<RawTcpSocket> {
     let raw_tcp_socket = {
-        let rx_buffer = smoltcp::socket::tcp::SocketBuffer::new(vec![0u8; TCP_RECV_BUF_LEN]);
-        let tx_buffer = smoltcp::socket::tcp::SocketBuffer::new(vec![0u8; TCP_SEND_BUF_LEN]);
+        let rx_buffer = smoltcp::socket::tcp::SocketBuffer::new(vec![0u8; recv_buf_len]);
+        let tx_buffer = smoltcp::socket::tcp::SocketBuffer::new(vec![0u8; send_buf_len]);
         RawTcpSocket::new(rx_buffer, tx_buffer)
     };
     Box::new(raw_tcp_socket)
@@ -42,7 +42,8 @@ pub(super) fn new_udp_socket() -> Box<RawUdpSocket> {
 // abnormally (see <https://github.com/asterinas/asterinas/pull/1396>). So the socket buffer size
 // is increased from 64K to 128K.
 //
-// TODO: Consider allowing user programs to set the socket buffer length via `setsockopt` system calls.
+// These are the default (and minimum) sizes. User programs can request larger buffers via the
+// `SO_RCVBUF` and `SO_SNDBUF` socket options before connecting or listening.
 pub const TCP_RECV_BUF_LEN: usize = 65536 * 2;
 pub const TCP_SEND_BUF_LEN: usize = 65536 * 2;
 
@@ -1,7 +1,7 @@
 // SPDX-License-Identifier: MPL-2.0
 
-//! This module defines the socket table, which manages all TCP and UDP sockets,
-//! for efficiently inserting, looking up, and removing sockets.
+//! This module defines the socket table, which manages all TCP, UDP, raw IP, and link-layer
+//! sockets, for efficiently inserting, looking up, and removing sockets.
 
 use alloc::{boxed::Box, sync::Arc, vec::Vec};
 use core::net::Ipv4Addr;
@@ -12,7 +12,7 @@ use smoltcp::wire::{IpAddress, IpEndpoint, IpListenEndpoint};
 
 use crate::{
     ext::Ext,
-    socket::{TcpConnectionBg, TcpListenerBg, UdpSocketBg},
+    socket::{LinkSocketBg, RawIpSocketBg, TcpConnectionBg, TcpListenerBg, UdpSocketBg},
     wire::PortNum,
 };
 
@@ -103,26 +103,33 @@ const fn hash_local_remote(
     remote_addr: IpAddress,
     remote_port: PortNum,
 ) -> SocketHash {
-    // FIXME: Deal with IPv6 addresses once IPv6 is supported.
-    let IpAddress::Ipv4(local_ipv4) = local_addr;
-    let IpAddress::Ipv4(remote_ipv4) = remote_addr;
-
     jhash_3vals(
-        local_ipv4.to_bits(),
-        remote_ipv4.to_bits(),
+        fold_addr(local_addr),
+        fold_addr(remote_addr),
         (local_port as u32).wrapping_shl(16) | remote_port as u32,
         HASH_SECRET.wrapping_add(NET_HASHMIX),
     )
 }
 
 const fn hash_addr_port(addr: IpAddress, port: PortNum) -> SocketHash {
-    // FIXME: Deal with IPv6 addresses once IPv6 is supported.
-    let IpAddress::Ipv4(ipv4_addr) = addr;
+    jhash_1vals(fold_addr(addr), NET_HASHMIX) ^ (port as u32)
+}
 
-    jhash_1vals(ipv4_addr.to_bits(), NET_HASHMIX) ^ (port as u32)
+/// Folds an IP address into a 32-bit value for hashing.
+///
+/// Like Linux, an IPv6 address is folded by XORing its four 32-bit words. See
+/// <https://elixir.bootlin.com/linux/v6.0.9/source/include/net/ipv6.h#L722>.
+const fn fold_addr(addr: IpAddress) -> u32 {
+    match addr {
+        IpAddress::Ipv4(ipv4_addr) => ipv4_addr.to_bits(),
+        IpAddress::Ipv6(ipv6_addr) => {
+            let bits = ipv6_addr.to_bits();
+            (bits as u32) ^ ((bits >> 32) as u32) ^ ((bits >> 64) as u32) ^ ((bits >> 96) as u32)
+        }
+    }
 }
 
-/// The socket table manages TCP and UDP sockets.
+/// The socket table manages TCP, UDP, raw IP, and link-layer sockets.
 ///
 /// Unlike the Linux inet hashtable, which is shared across a single network namespace,
 /// this table is currently limited to a single interface.
@@ -142,6 +149,10 @@ pub(crate) struct SocketTable<E: Ext> {
     // Note that multiple UDP sockets can be bound to the same address,
     // so we cannot use (addr, port) as a _unique_ key for UDP sockets.
     udp_sockets: Vec<Arc<UdpSocketBg<E>>>,
+    // Raw IP sockets receive all packets with the matching protocol, so a list is sufficient.
+    raw_sockets: Vec<Arc<RawIpSocketBg<E>>>,
+    // Link-layer sockets capture all frames, so a list is sufficient.
+    link_sockets: Vec<Arc<LinkSocketBg<E>>>,
 }
 
 // On Linux, the number of buckets is determined at runtime based on the available memory.
@@ -166,11 +177,15 @@ impl<E: Ext> SocketTable<E> {
             .collect();
 
         let udp_sockets = Vec::new();
+        let raw_sockets = Vec::new();
+        let link_sockets = Vec::new();
 
         Self {
             listener_buckets,
             connection_buckets,
             udp_sockets,
+            raw_sockets,
+            link_sockets,
         }
     }
 
@@ -234,6 +249,22 @@ impl<E: Ext> SocketTable<E> {
         self.udp_sockets.push(udp_socket);
     }
 
+    pub(crate) fn insert_raw_socket(&mut self, raw_socket: Arc<RawIpSocketBg<E>>) {
+        debug_assert!(!self
+            .raw_sockets
+            .iter()
+            .any(|socket| Arc::ptr_eq(socket, &raw_socket)));
+        self.raw_sockets.push(raw_socket);
+    }
+
+    pub(crate) fn insert_link_socket(&mut self, link_socket: Arc<LinkSocketBg<E>>) {
+        debug_assert!(!self
+            .link_sockets
+            .iter()
+            .any(|socket| Arc::ptr_eq(socket, &link_socket)));
+        self.link_sockets.push(link_socket);
+    }
+
     pub(crate) fn lookup_listener(&self, key: &ListenerKey) -> Option<&Arc<TcpListenerBg<E>>> {
         let bucket = {
             let hash = key.hash();
@@ -312,6 +343,36 @@ impl<E: Ext> SocketTable<E> {
     pub(crate) fn udp_socket_iter(&self) -> impl Iterator<Item = &Arc<UdpSocketBg<E>>> {
         self.udp_sockets.iter()
     }
+
+    pub(crate) fn remove_raw_socket(
+        &mut self,
+        socket: &Arc<RawIpSocketBg<E>>,
+    ) -> Option<Arc<RawIpSocketBg<E>>> {
+        let index = self
+            .raw_sockets
+            .iter()
+            .position(|raw_socket| Arc::ptr_eq(raw_socket, socket))?;
+        Some(self.raw_sockets.swap_remove(index))
+    }
+
+    pub(crate) fn raw_socket_iter(&self) -> impl Iterator<Item = &Arc<RawIpSocketBg<E>>> {
+        self.raw_sockets.iter()
+    }
+
+    pub(crate) fn remove_link_socket(
+        &mut self,
+        socket: &Arc<LinkSocketBg<E>>,
+    ) -> Option<Arc<LinkSocketBg<E>>> {
+        let index = self
+            .link_sockets
+            .iter()
+            .position(|link_socket| Arc::ptr_eq(link_socket, socket))?;
+        Some(self.link_sockets.swap_remove(index))
+    }
+
+    pub(crate) fn link_socket_iter(&self) -> impl Iterator<Item = &Arc<LinkSocketBg<E>>> {
+        self.link_sockets.iter()
+    }
 }
 
 impl<E: Ext> Default for SocketTable<E> {
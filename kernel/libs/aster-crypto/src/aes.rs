@@ -0,0 +1,292 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The AES block cipher with 128-bit, 192-bit, and 256-bit keys.
+//!
+//! Like the generic implementation of Linux, this implementation looks up the S-box with the
+//! secret bytes as indices, so it may leak the secrets through the cache timing. Hardware
+//! implementations should be preferred if they are available.
+//!
+//! Reference: <https://nvlpubs.nist.gov/nistpubs/FIPS/NIST.FIPS.197-upd1.pdf>.
+
+/// The length of AES blocks.
+pub const BLOCK_LEN: usize = 16;
+
+/// The maximum number of rounds (i.e., the number of rounds of AES-256).
+pub const MAX_ROUNDS: usize = 14;
+
+/// An AES block.
+pub type Block = [u8; BLOCK_LEN];
+
+/// The S-box, which is computed from the multiplicative inverse in GF(2^8).
+const SBOX: [u8; 256] = build_sbox();
+const INV_SBOX: [u8; 256] = build_inv_sbox();
+
+const fn gf_mul_const(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+        a = (a << 1) ^ if a & 0x80 != 0 { 0x1b } else { 0 };
+        b >>= 1;
+    }
+    product
+}
+
+const fn build_sbox() -> [u8; 256] {
+    let mut sbox = [0u8; 256];
+    let mut x = 0;
+    while x < 256 {
+        // The inverse is x^254, and the inverse of zero is defined to be zero.
+        let mut inverse = 1u8;
+        let mut i = 0;
+        while i < 254 {
+            inverse = gf_mul_const(inverse, x as u8);
+            i += 1;
+        }
+        if x == 0 {
+            inverse = 0;
+        }
+
+        let b = inverse;
+        sbox[x] =
+            b ^ b.rotate_left(1) ^ b.rotate_left(2) ^ b.rotate_left(3) ^ b.rotate_left(4) ^ 0x63;
+        x += 1;
+    }
+    sbox
+}
+
+const fn build_inv_sbox() -> [u8; 256] {
+    let mut inv_sbox = [0u8; 256];
+    let mut x = 0;
+    while x < 256 {
+        inv_sbox[SBOX[x] as usize] = x as u8;
+        x += 1;
+    }
+    inv_sbox
+}
+
+/// Multiplies `x` by 2 in GF(2^8) without branches.
+fn xtime(x: u8) -> u8 {
+    (x << 1) ^ (0x1b & 0u8.wrapping_sub(x >> 7))
+}
+
+/// Multiplies `a` by `b` in GF(2^8) without secret-dependent branches.
+fn gf_mul(mut a: u8, b: u8) -> u8 {
+    let mut product = 0;
+    for i in 0..8 {
+        product ^= a & 0u8.wrapping_sub((b >> i) & 1);
+        a = xtime(a);
+    }
+    product
+}
+
+/// An AES key, which has been expanded into the round keys.
+#[derive(Clone)]
+pub struct Aes {
+    round_keys: [Block; MAX_ROUNDS + 1],
+    rounds: usize,
+}
+
+impl Aes {
+    /// Expands the key, which must be 16, 24, or 32 bytes long.
+    pub fn new(key: &[u8]) -> Option<Self> {
+        let nk = match key.len() {
+            16 | 24 | 32 => key.len() / 4,
+            _ => return None,
+        };
+        let rounds = nk + 6;
+        let nr_words = 4 * (rounds + 1);
+
+        let mut words = [[0u8; 4]; 4 * (MAX_ROUNDS + 1)];
+        for (word, bytes) in words.iter_mut().zip(key.chunks_exact(4)) {
+            word.copy_from_slice(bytes);
+        }
+
+        let mut rcon = 1u8;
+        for i in nk..nr_words {
+            let mut temp = words[i - 1];
+            if i % nk == 0 {
+                temp.rotate_left(1);
+                temp = temp.map(|byte| SBOX[byte as usize]);
+                temp[0] ^= rcon;
+                rcon = xtime(rcon);
+            } else if nk > 6 && i % nk == 4 {
+                temp = temp.map(|byte| SBOX[byte as usize]);
+            }
+            for j in 0..4 {
+                words[i][j] = words[i - nk][j] ^ temp[j];
+            }
+        }
+
+        let mut round_keys = [[0u8; BLOCK_LEN]; MAX_ROUNDS + 1];
+        for (round_key, round_words) in round_keys.iter_mut().zip(words.chunks_exact(4)) {
+            for (bytes, word) in round_key.chunks_exact_mut(4).zip(round_words) {
+                bytes.copy_from_slice(word);
+            }
+        }
+
+        Some(Self { round_keys, rounds })
+    }
+
+    /// Returns the number of rounds.
+    pub fn rounds(&self) -> usize {
+        self.rounds
+    }
+
+    /// Returns the round keys, from the one added before the first round to the one added
+    /// after the last round.
+    pub fn round_keys(&self) -> &[Block] {
+        &self.round_keys[..=self.rounds]
+    }
+
+    /// Encrypts a block in place.
+    pub fn encrypt_block(&self, block: &mut Block) {
+        add_round_key(block, &self.round_keys[0]);
+        for round in 1..self.rounds {
+            sub_bytes(block, &SBOX);
+            shift_rows(block);
+            mix_columns(block);
+            add_round_key(block, &self.round_keys[round]);
+        }
+        sub_bytes(block, &SBOX);
+        shift_rows(block);
+        add_round_key(block, &self.round_keys[self.rounds]);
+    }
+
+    /// Decrypts a block in place.
+    pub fn decrypt_block(&self, block: &mut Block) {
+        add_round_key(block, &self.round_keys[self.rounds]);
+        for round in (1..self.rounds).rev() {
+            inv_shift_rows(block);
+            sub_bytes(block, &INV_SBOX);
+            add_round_key(block, &self.round_keys[round]);
+            inv_mix_columns(block);
+        }
+        inv_shift_rows(block);
+        sub_bytes(block, &INV_SBOX);
+        add_round_key(block, &self.round_keys[0]);
+    }
+}
+
+impl core::fmt::Debug for Aes {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        // Never show the keys.
+        f.debug_struct("Aes")
+            .field("rounds", &self.rounds)
+            .finish_non_exhaustive()
+    }
+}
+
+fn add_round_key(block: &mut Block, round_key: &Block) {
+    for (byte, key_byte) in block.iter_mut().zip(round_key) {
+        *byte ^= key_byte;
+    }
+}
+
+fn sub_bytes(block: &mut Block, sbox: &[u8; 256]) {
+    for byte in block.iter_mut() {
+        *byte = sbox[*byte as usize];
+    }
+}
+
+// The block is stored in the column-major order, i.e., `block[row + 4 * column]`.
+
+fn shift_rows(block: &mut Block) {
+    let old = *block;
+    for row in 1..4 {
+        for column in 0..4 {
+            block[row + 4 * column] = old[row + 4 * ((column + row) % 4)];
+        }
+    }
+}
+
+fn inv_shift_rows(block: &mut Block) {
+    let old = *block;
+    for row in 1..4 {
+        for column in 0..4 {
+            block[row + 4 * ((column + row) % 4)] = old[row + 4 * column];
+        }
+    }
+}
+
+fn mix_columns(block: &mut Block) {
+    for column in block.chunks_exact_mut(4) {
+        let [a0, a1, a2, a3] = [column[0], column[1], column[2], column[3]];
+        let all = a0 ^ a1 ^ a2 ^ a3;
+        column[0] ^= all ^ xtime(a0 ^ a1);
+        column[1] ^= all ^ xtime(a1 ^ a2);
+        column[2] ^= all ^ xtime(a2 ^ a3);
+        column[3] ^= all ^ xtime(a3 ^ a0);
+    }
+}
+
+fn inv_mix_columns(block: &mut Block) {
+    for column in block.chunks_exact_mut(4) {
+        let a = [column[0], column[1], column[2], column[3]];
+        for (i, byte) in column.iter_mut().enumerate() {
+            *byte = gf_mul(a[i], 14)
+                ^ gf_mul(a[(i + 1) % 4], 11)
+                ^ gf_mul(a[(i + 2) % 4], 13)
+                ^ gf_mul(a[(i + 3) % 4], 9);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::hex;
+
+    const PLAINTEXT: &str = "00112233445566778899aabbccddeeff";
+
+    fn check(key: &[u8], ciphertext: &str) {
+        let aes = Aes::new(key).unwrap();
+
+        let mut block = hex::<BLOCK_LEN>(PLAINTEXT);
+        aes.encrypt_block(&mut block);
+        assert_eq!(block, hex::<BLOCK_LEN>(ciphertext));
+        aes.decrypt_block(&mut block);
+        assert_eq!(block, hex::<BLOCK_LEN>(PLAINTEXT));
+    }
+
+    #[test]
+    fn test_sbox() {
+        assert_eq!(SBOX[0x00], 0x63);
+        assert_eq!(SBOX[0x53], 0xed);
+        assert_eq!(INV_SBOX[0x63], 0x00);
+    }
+
+    #[test]
+    fn test_aes128() {
+        check(
+            &hex::<16>("000102030405060708090a0b0c0d0e0f"),
+            "69c4e0d86a7b0430d8cdb78070b4c55a",
+        );
+    }
+
+    #[test]
+    fn test_aes192() {
+        check(
+            &hex::<24>("000102030405060708090a0b0c0d0e0f1011121314151617"),
+            "dda97ca4864cdfe06eaf70a0ec0d7191",
+        );
+    }
+
+    #[test]
+    fn test_aes256() {
+        check(
+            &hex::<32>("000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f"),
+            "8ea2b7ca516745bfeafc49904b496089",
+        );
+        assert_eq!(
+            Aes::new(&[0u8; 32]).unwrap().round_keys().len(),
+            MAX_ROUNDS + 1
+        );
+    }
+
+    #[test]
+    fn test_invalid_key_len() {
+        assert!(Aes::new(&[0u8; 20]).is_none());
+    }
+}
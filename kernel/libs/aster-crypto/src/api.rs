@@ -0,0 +1,618 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The registry of cryptographic algorithms (i.e., the crypto API).
+//!
+//! The users look up algorithms by their names (e.g., `sha256`) instead of calling the
+//! implementations directly. Like Linux, an algorithm can be implemented by multiple drivers
+//! (e.g., `sha256-generic` and a hardware-accelerated driver), and the lookup returns the
+//! driver with the highest priority. The software implementations in this crate are always
+//! registered with [`GENERIC_PRIORITY`].
+//!
+//! Reference: <https://docs.kernel.org/crypto/architecture.html>.
+
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+
+use spin::{Once, RwLock};
+
+pub use crate::chacha20poly1305::AuthError;
+use crate::{
+    aes::{self, Aes},
+    blake2s::{self, Blake2s},
+    chacha20poly1305,
+    sha2::{
+        self, Compress256, Compress512, SHA224_DIGEST_LEN, SHA256_BLOCK_LEN, SHA256_DIGEST_LEN,
+        SHA384_DIGEST_LEN, SHA512_BLOCK_LEN, SHA512_DIGEST_LEN, Sha256, Sha512,
+    },
+};
+
+/// The priority of the software implementations.
+pub const GENERIC_PRIORITY: u32 = 100;
+
+/// A hash function.
+pub trait Hash: Send + Sync {
+    /// Returns the length of the digests.
+    fn digest_len(&self) -> usize;
+
+    /// Returns the length of the blocks, which is used by constructions such as HMAC.
+    fn block_len(&self) -> usize;
+
+    /// Creates the initial state.
+    fn new_state(&self) -> Box<dyn HashState>;
+
+    /// Computes the digest of `data` and writes it to `out`.
+    ///
+    /// The length of `out` must be [`Self::digest_len`].
+    fn digest(&self, data: &[u8], out: &mut [u8]) {
+        let mut state = self.new_state();
+        state.update(data);
+        state.finalize(out);
+    }
+}
+
+/// The state of a hash function.
+pub trait HashState: Send {
+    /// Absorbs `data` into the state.
+    fn update(&mut self, data: &[u8]);
+
+    /// Writes the digest to `out`, whose length must be [`Hash::digest_len`].
+    fn finalize(self: Box<Self>, out: &mut [u8]);
+}
+
+/// A block cipher.
+pub trait BlockCipher: Send + Sync {
+    /// Returns the length of the blocks.
+    fn block_len(&self) -> usize;
+
+    /// Returns the supported lengths of the keys.
+    fn key_lens(&self) -> &'static [usize];
+
+    /// Expands the key, or returns `None` if the length of the key is not supported.
+    fn new_key(&self, key: &[u8]) -> Option<Box<dyn BlockCipherKey>>;
+}
+
+/// An expanded key of a block cipher.
+pub trait BlockCipherKey: Send + Sync {
+    /// Encrypts a block in place.
+    ///
+    /// The length of `block` must be [`BlockCipher::block_len`].
+    fn encrypt_block(&self, block: &mut [u8]);
+
+    /// Decrypts a block in place.
+    ///
+    /// The length of `block` must be [`BlockCipher::block_len`].
+    fn decrypt_block(&self, block: &mut [u8]);
+}
+
+/// An authenticated encryption with associated data (AEAD).
+pub trait Aead: Send + Sync {
+    /// Returns the length of the keys.
+    fn key_len(&self) -> usize;
+
+    /// Returns the length of the nonces.
+    fn nonce_len(&self) -> usize;
+
+    /// Returns the length of the tags.
+    fn tag_len(&self) -> usize;
+
+    /// Encrypts `buffer` in place and writes the tag that authenticates both the ciphertext
+    /// and the associated data `aad` to `tag`.
+    ///
+    /// The lengths of `key`, `nonce`, and `tag` must be [`Self::key_len`],
+    /// [`Self::nonce_len`], and [`Self::tag_len`], respectively.
+    fn seal(&self, key: &[u8], nonce: &[u8], aad: &[u8], buffer: &mut [u8], tag: &mut [u8]);
+
+    /// Authenticates the ciphertext in `buffer` and the associated data `aad` with `tag`, and
+    /// then decrypts `buffer` in place.
+    ///
+    /// If the authentication fails, `buffer` is left unchanged. The lengths of the arguments
+    /// are the same as those of [`Self::seal`].
+    fn open(
+        &self,
+        key: &[u8],
+        nonce: &[u8],
+        aad: &[u8],
+        buffer: &mut [u8],
+        tag: &[u8],
+    ) -> Result<(), AuthError>;
+}
+
+/// An implementation of an algorithm.
+#[derive(Clone)]
+pub enum Algorithm {
+    Hash(Arc<dyn Hash>),
+    BlockCipher(Arc<dyn BlockCipher>),
+    Aead(Arc<dyn Aead>),
+}
+
+impl Algorithm {
+    /// Returns the type of the algorithm, as shown in `/proc/crypto` of Linux.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Hash(_) => "shash",
+            Self::BlockCipher(_) => "cipher",
+            Self::Aead(_) => "aead",
+        }
+    }
+}
+
+/// A driver, which implements an algorithm.
+#[derive(Clone)]
+pub struct Driver {
+    /// The name of the algorithm (e.g., `sha256`)
+    pub name: &'static str,
+    /// The name of the driver, which is unique (e.g., `sha256-generic`)
+    pub driver_name: &'static str,
+    /// The priority of the driver
+    pub priority: u32,
+    /// The implementation of the algorithm
+    pub algorithm: Algorithm,
+}
+
+/// An error indicating that a driver with the same name has been registered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AlreadyRegistered;
+
+static DRIVERS: RwLock<Vec<Driver>> = RwLock::new(Vec::new());
+
+/// Registers a driver.
+pub fn register(driver: Driver) -> Result<(), AlreadyRegistered> {
+    register_generic_drivers();
+
+    let mut drivers = DRIVERS.write();
+    if drivers
+        .iter()
+        .any(|registered| registered.driver_name == driver.driver_name)
+    {
+        return Err(AlreadyRegistered);
+    }
+    drivers.push(driver);
+
+    Ok(())
+}
+
+/// Returns all the registered drivers.
+pub fn drivers() -> Vec<Driver> {
+    register_generic_drivers();
+
+    DRIVERS.read().clone()
+}
+
+/// Finds the driver of the algorithm named `name` with the highest priority.
+pub fn find(name: &str) -> Option<Algorithm> {
+    register_generic_drivers();
+
+    DRIVERS
+        .read()
+        .iter()
+        .filter(|driver| driver.name == name)
+        // The earliest driver wins if multiple drivers have the same priority.
+        .rev()
+        .max_by_key(|driver| driver.priority)
+        .map(|driver| driver.algorithm.clone())
+}
+
+/// Finds the hash function named `name`.
+pub fn find_hash(name: &str) -> Option<Arc<dyn Hash>> {
+    match find(name)? {
+        Algorithm::Hash(hash) => Some(hash),
+        _ => None,
+    }
+}
+
+/// Finds the block cipher named `name`.
+pub fn find_block_cipher(name: &str) -> Option<Arc<dyn BlockCipher>> {
+    match find(name)? {
+        Algorithm::BlockCipher(cipher) => Some(cipher),
+        _ => None,
+    }
+}
+
+/// Finds the AEAD named `name`.
+pub fn find_aead(name: &str) -> Option<Arc<dyn Aead>> {
+    match find(name)? {
+        Algorithm::Aead(aead) => Some(aead),
+        _ => None,
+    }
+}
+
+fn register_generic_drivers() {
+    static GENERIC_DRIVERS: Once = Once::new();
+
+    GENERIC_DRIVERS.call_once(|| {
+        let generic = |name, driver_name, algorithm| Driver {
+            name,
+            driver_name,
+            priority: GENERIC_PRIORITY,
+            algorithm,
+        };
+
+        DRIVERS.write().extend([
+            generic(
+                "sha224",
+                "sha224-generic",
+                Algorithm::Hash(Arc::new(Sha256Hash::new(
+                    SHA224_DIGEST_LEN,
+                    sha2::compress256_generic,
+                ))),
+            ),
+            generic(
+                "sha256",
+                "sha256-generic",
+                Algorithm::Hash(Arc::new(Sha256Hash::new(
+                    SHA256_DIGEST_LEN,
+                    sha2::compress256_generic,
+                ))),
+            ),
+            generic(
+                "sha384",
+                "sha384-generic",
+                Algorithm::Hash(Arc::new(Sha512Hash::new(
+                    SHA384_DIGEST_LEN,
+                    sha2::compress512_generic,
+                ))),
+            ),
+            generic(
+                "sha512",
+                "sha512-generic",
+                Algorithm::Hash(Arc::new(Sha512Hash::new(
+                    SHA512_DIGEST_LEN,
+                    sha2::compress512_generic,
+                ))),
+            ),
+            generic(
+                "blake2s-256",
+                "blake2s-256-generic",
+                Algorithm::Hash(Arc::new(Blake2sHash)),
+            ),
+            generic(
+                "aes",
+                "aes-generic",
+                Algorithm::BlockCipher(Arc::new(AesCipher)),
+            ),
+            generic(
+                "rfc7539(chacha20,poly1305)",
+                "rfc7539-chacha20-poly1305-generic",
+                Algorithm::Aead(Arc::new(ChaCha20Poly1305Aead)),
+            ),
+        ]);
+    });
+}
+
+/// SHA-224 or SHA-256 with a given compression function.
+pub struct Sha256Hash {
+    digest_len: usize,
+    compress: Compress256,
+}
+
+impl Sha256Hash {
+    /// Creates SHA-224 or SHA-256, depending on `digest_len`.
+    ///
+    /// See [`Sha256::with_compress`] for the requirements of the arguments.
+    pub fn new(digest_len: usize, compress: Compress256) -> Self {
+        Self {
+            digest_len,
+            compress,
+        }
+    }
+}
+
+impl Hash for Sha256Hash {
+    fn digest_len(&self) -> usize {
+        self.digest_len
+    }
+
+    fn block_len(&self) -> usize {
+        SHA256_BLOCK_LEN
+    }
+
+    fn new_state(&self) -> Box<dyn HashState> {
+        Box::new(Sha256::with_compress(self.digest_len, self.compress))
+    }
+}
+
+impl HashState for Sha256 {
+    fn update(&mut self, data: &[u8]) {
+        Sha256::update(self, data);
+    }
+
+    fn finalize(self: Box<Self>, out: &mut [u8]) {
+        Sha256::finalize(*self, out);
+    }
+}
+
+/// SHA-384 or SHA-512 with a given compression function.
+pub struct Sha512Hash {
+    digest_len: usize,
+    compress: Compress512,
+}
+
+impl Sha512Hash {
+    /// Creates SHA-384 or SHA-512, depending on `digest_len`.
+    ///
+    /// See [`Sha512::with_compress`] for the requirements of the arguments.
+    pub fn new(digest_len: usize, compress: Compress512) -> Self {
+        Self {
+            digest_len,
+            compress,
+        }
+    }
+}
+
+impl Hash for Sha512Hash {
+    fn digest_len(&self) -> usize {
+        self.digest_len
+    }
+
+    fn block_len(&self) -> usize {
+        SHA512_BLOCK_LEN
+    }
+
+    fn new_state(&self) -> Box<dyn HashState> {
+        Box::new(Sha512::with_compress(self.digest_len, self.compress))
+    }
+}
+
+impl HashState for Sha512 {
+    fn update(&mut self, data: &[u8]) {
+        Sha512::update(self, data);
+    }
+
+    fn finalize(self: Box<Self>, out: &mut [u8]) {
+        Sha512::finalize(*self, out);
+    }
+}
+
+struct Blake2sHash;
+
+impl Hash for Blake2sHash {
+    fn digest_len(&self) -> usize {
+        blake2s::MAX_OUT_LEN
+    }
+
+    fn block_len(&self) -> usize {
+        blake2s::BLOCK_LEN
+    }
+
+    fn new_state(&self) -> Box<dyn HashState> {
+        Box::new(Blake2s::new(blake2s::MAX_OUT_LEN))
+    }
+}
+
+impl HashState for Blake2s {
+    fn update(&mut self, data: &[u8]) {
+        Blake2s::update(self, data);
+    }
+
+    fn finalize(self: Box<Self>, out: &mut [u8]) {
+        Blake2s::finalize(*self, out);
+    }
+}
+
+struct AesCipher;
+
+impl BlockCipher for AesCipher {
+    fn block_len(&self) -> usize {
+        aes::BLOCK_LEN
+    }
+
+    fn key_lens(&self) -> &'static [usize] {
+        &[16, 24, 32]
+    }
+
+    fn new_key(&self, key: &[u8]) -> Option<Box<dyn BlockCipherKey>> {
+        Some(Box::new(Aes::new(key)?))
+    }
+}
+
+impl BlockCipherKey for Aes {
+    fn encrypt_block(&self, block: &mut [u8]) {
+        Aes::encrypt_block(self, block.try_into().unwrap());
+    }
+
+    fn decrypt_block(&self, block: &mut [u8]) {
+        Aes::decrypt_block(self, block.try_into().unwrap());
+    }
+}
+
+struct ChaCha20Poly1305Aead;
+
+impl Aead for ChaCha20Poly1305Aead {
+    fn key_len(&self) -> usize {
+        chacha20poly1305::KEY_LEN
+    }
+
+    fn nonce_len(&self) -> usize {
+        chacha20poly1305::NONCE_LEN
+    }
+
+    fn tag_len(&self) -> usize {
+        chacha20poly1305::TAG_LEN
+    }
+
+    fn seal(&self, key: &[u8], nonce: &[u8], aad: &[u8], buffer: &mut [u8], tag: &mut [u8]) {
+        tag.copy_from_slice(&chacha20poly1305::seal(
+            key.try_into().unwrap(),
+            nonce.try_into().unwrap(),
+            aad,
+            buffer,
+        ));
+    }
+
+    fn open(
+        &self,
+        key: &[u8],
+        nonce: &[u8],
+        aad: &[u8],
+        buffer: &mut [u8],
+        tag: &[u8],
+    ) -> Result<(), AuthError> {
+        chacha20poly1305::open(
+            key.try_into().unwrap(),
+            nonce.try_into().unwrap(),
+            aad,
+            buffer,
+            tag.try_into().unwrap(),
+        )
+    }
+}
+
+/// The HMAC construction over a hash function.
+///
+/// Reference: <https://datatracker.ietf.org/doc/html/rfc2104>.
+pub struct Hmac {
+    inner: Box<dyn HashState>,
+    outer: Box<dyn HashState>,
+    digest_len: usize,
+}
+
+impl Hmac {
+    /// Creates an HMAC state with the hash function and the key.
+    pub fn new(hash: &dyn Hash, key: &[u8]) -> Self {
+        let block_len = hash.block_len();
+
+        let mut padded_key = alloc::vec![0u8; block_len];
+        if key.len() > block_len {
+            hash.digest(key, &mut padded_key[..hash.digest_len()]);
+        } else {
+            padded_key[..key.len()].copy_from_slice(key);
+        }
+
+        let mut inner = hash.new_state();
+        let mut outer = hash.new_state();
+        for byte in padded_key.iter_mut() {
+            *byte ^= 0x36;
+        }
+        inner.update(&padded_key);
+        for byte in padded_key.iter_mut() {
+            *byte ^= 0x36 ^ 0x5c;
+        }
+        outer.update(&padded_key);
+
+        Self {
+            inner,
+            outer,
+            digest_len: hash.digest_len(),
+        }
+    }
+
+    /// Returns the length of the MACs, which is that of the digests of the hash function.
+    pub fn mac_len(&self) -> usize {
+        self.digest_len
+    }
+
+    /// Absorbs `data` into the state.
+    pub fn update(&mut self, data: &[u8]) {
+        self.inner.update(data);
+    }
+
+    /// Writes the MAC to `out`, whose length must be [`Self::mac_len`].
+    pub fn finalize(self, out: &mut [u8]) {
+        self.inner.finalize(out);
+
+        let mut outer = self.outer;
+        outer.update(out);
+        outer.finalize(out);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::hex;
+
+    #[test]
+    fn test_find() {
+        let hash = find_hash("sha256").unwrap();
+        let mut out = [0u8; SHA256_DIGEST_LEN];
+        hash.digest(b"abc", &mut out);
+        assert_eq!(out, sha2::sha256(b"abc"));
+
+        assert!(find_block_cipher("aes").is_some());
+        assert!(find_aead("rfc7539(chacha20,poly1305)").is_some());
+        assert!(find_hash("aes").is_none());
+        assert!(find("md4").is_none());
+    }
+
+    #[test]
+    fn test_priority() {
+        struct Zero;
+
+        impl Hash for Zero {
+            fn digest_len(&self) -> usize {
+                4
+            }
+
+            fn block_len(&self) -> usize {
+                4
+            }
+
+            fn new_state(&self) -> Box<dyn HashState> {
+                Box::new(ZeroState)
+            }
+        }
+
+        struct ZeroState;
+
+        impl HashState for ZeroState {
+            fn update(&mut self, _data: &[u8]) {}
+
+            fn finalize(self: Box<Self>, out: &mut [u8]) {
+                out.fill(0);
+            }
+        }
+
+        let driver = |driver_name, priority| Driver {
+            name: "zero",
+            driver_name,
+            priority,
+            algorithm: Algorithm::Hash(Arc::new(Zero)),
+        };
+        register(driver("zero-low", 50)).unwrap();
+        register(driver("zero-high", 200)).unwrap();
+        assert_eq!(register(driver("zero-low", 50)), Err(AlreadyRegistered));
+
+        let found = find_hash("zero").unwrap();
+        let high = drivers()
+            .into_iter()
+            .find(|driver| driver.driver_name == "zero-high")
+            .unwrap();
+        let Algorithm::Hash(high) = high.algorithm else {
+            panic!("the driver is not a hash function");
+        };
+        assert!(Arc::ptr_eq(&found, &high));
+    }
+
+    fn hmac_sha256(key: &[u8], data: &[u8]) -> [u8; SHA256_DIGEST_LEN] {
+        let mut hmac = Hmac::new(&*find_hash("sha256").unwrap(), key);
+        hmac.update(data);
+
+        let mut out = [0u8; SHA256_DIGEST_LEN];
+        hmac.finalize(&mut out);
+        out
+    }
+
+    // The test vectors are from RFC 4231.
+
+    #[test]
+    fn test_hmac_sha256() {
+        assert_eq!(
+            hmac_sha256(&[0x0b; 20], b"Hi There"),
+            hex("b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7")
+        );
+        assert_eq!(
+            hmac_sha256(b"Jefe", b"what do ya want for nothing?"),
+            hex("5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843")
+        );
+    }
+
+    #[test]
+    fn test_hmac_long_key() {
+        assert_eq!(
+            hmac_sha256(
+                &[0xaa; 131],
+                b"Test Using Larger Than Block-Size Key - Hash Key First"
+            ),
+            hex("60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54")
+        );
+    }
+}
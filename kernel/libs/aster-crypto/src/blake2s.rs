@@ -0,0 +1,327 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The BLAKE2s hash function.
+//!
+//! BLAKE2s produces digests of up to 32 bytes and optionally takes a key of up to 32 bytes, in
+//! which case it works as a MAC. It can also be used with the generic HMAC construction (see
+//! [`Hmac`]), which is required by some protocols (e.g., WireGuard).
+//!
+//! Reference: <https://datatracker.ietf.org/doc/html/rfc7693>.
+
+/// The maximum length (and the default length) of BLAKE2s digests.
+pub const MAX_OUT_LEN: usize = 32;
+/// The maximum length of BLAKE2s keys.
+pub const MAX_KEY_LEN: usize = 32;
+/// The length of BLAKE2s blocks.
+pub const BLOCK_LEN: usize = 64;
+
+const IV: [u32; 8] = [
+    0x6a09_e667,
+    0xbb67_ae85,
+    0x3c6e_f372,
+    0xa54f_f53a,
+    0x510e_527f,
+    0x9b05_688c,
+    0x1f83_d9ab,
+    0x5be0_cd19,
+];
+
+const SIGMA: [[usize; 16]; 10] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+    [11, 8, 12, 0, 5, 2, 15, 13, 10, 14, 3, 6, 7, 1, 9, 4],
+    [7, 9, 3, 1, 13, 12, 11, 14, 2, 6, 5, 10, 4, 0, 15, 8],
+    [9, 0, 5, 7, 2, 4, 10, 15, 14, 1, 11, 12, 6, 8, 3, 13],
+    [2, 12, 6, 10, 0, 11, 8, 3, 4, 13, 7, 5, 15, 14, 1, 9],
+    [12, 5, 1, 15, 14, 13, 4, 10, 0, 7, 6, 3, 9, 2, 8, 11],
+    [13, 11, 7, 14, 12, 1, 3, 9, 5, 0, 15, 4, 8, 6, 2, 10],
+    [6, 15, 14, 9, 11, 3, 0, 8, 12, 2, 13, 7, 1, 4, 10, 5],
+    [10, 2, 8, 4, 7, 6, 1, 5, 15, 11, 9, 14, 3, 12, 13, 0],
+];
+
+/// The state of a BLAKE2s computation.
+#[derive(Clone)]
+pub struct Blake2s {
+    h: [u32; 8],
+    /// The number of bytes that have been compressed
+    counter: u64,
+    /// The bytes that have not been compressed yet
+    ///
+    /// The last block is kept in the buffer until [`Blake2s::finalize`] is called, since it
+    /// must be compressed with the finalization flag.
+    buffer: [u8; BLOCK_LEN],
+    buffer_len: usize,
+    out_len: usize,
+}
+
+impl Blake2s {
+    /// Starts an unkeyed computation whose digest is `out_len` bytes long.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `out_len` is zero or greater than [`MAX_OUT_LEN`].
+    pub fn new(out_len: usize) -> Self {
+        Self::new_keyed(&[], out_len)
+    }
+
+    /// Starts a keyed computation whose digest is `out_len` bytes long.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `out_len` is zero or greater than [`MAX_OUT_LEN`], or if `key` is
+    /// longer than [`MAX_KEY_LEN`].
+    pub fn new_keyed(key: &[u8], out_len: usize) -> Self {
+        assert!((1..=MAX_OUT_LEN).contains(&out_len));
+        assert!(key.len() <= MAX_KEY_LEN);
+
+        let mut h = IV;
+        h[0] ^= 0x0101_0000 ^ ((key.len() as u32) << 8) ^ (out_len as u32);
+
+        let mut state = Self {
+            h,
+            counter: 0,
+            buffer: [0; BLOCK_LEN],
+            buffer_len: 0,
+            out_len,
+        };
+        if !key.is_empty() {
+            // The key is padded to a full block, which is processed as the first block.
+            state.buffer[..key.len()].copy_from_slice(key);
+            state.buffer_len = BLOCK_LEN;
+        }
+        state
+    }
+
+    /// Feeds more data into the computation.
+    pub fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            if self.buffer_len == BLOCK_LEN {
+                self.counter += BLOCK_LEN as u64;
+                let block = self.buffer;
+                self.compress(&block, false);
+                self.buffer_len = 0;
+            }
+
+            let len = (BLOCK_LEN - self.buffer_len).min(data.len());
+            self.buffer[self.buffer_len..self.buffer_len + len].copy_from_slice(&data[..len]);
+            self.buffer_len += len;
+            data = &data[len..];
+        }
+    }
+
+    /// Finishes the computation and writes the digest to `out`.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the length of `out` is not the digest length specified when the
+    /// computation starts.
+    pub fn finalize(mut self, out: &mut [u8]) {
+        assert_eq!(out.len(), self.out_len);
+
+        self.counter += self.buffer_len as u64;
+        self.buffer[self.buffer_len..].fill(0);
+        let block = self.buffer;
+        self.compress(&block, true);
+
+        for (bytes, word) in out.chunks_mut(4).zip(self.h.iter()) {
+            bytes.copy_from_slice(&word.to_le_bytes()[..bytes.len()]);
+        }
+    }
+
+    fn compress(&mut self, block: &[u8; BLOCK_LEN], is_last: bool) {
+        let mut m = [0u32; 16];
+        for (word, bytes) in m.iter_mut().zip(block.chunks_exact(4)) {
+            *word = u32::from_le_bytes(bytes.try_into().unwrap());
+        }
+
+        let mut v = [0u32; 16];
+        v[..8].copy_from_slice(&self.h);
+        v[8..].copy_from_slice(&IV);
+        v[12] ^= self.counter as u32;
+        v[13] ^= (self.counter >> 32) as u32;
+        if is_last {
+            v[14] = !v[14];
+        }
+
+        for s in SIGMA.iter() {
+            mix(&mut v, 0, 4, 8, 12, m[s[0]], m[s[1]]);
+            mix(&mut v, 1, 5, 9, 13, m[s[2]], m[s[3]]);
+            mix(&mut v, 2, 6, 10, 14, m[s[4]], m[s[5]]);
+            mix(&mut v, 3, 7, 11, 15, m[s[6]], m[s[7]]);
+            mix(&mut v, 0, 5, 10, 15, m[s[8]], m[s[9]]);
+            mix(&mut v, 1, 6, 11, 12, m[s[10]], m[s[11]]);
+            mix(&mut v, 2, 7, 8, 13, m[s[12]], m[s[13]]);
+            mix(&mut v, 3, 4, 9, 14, m[s[14]], m[s[15]]);
+        }
+
+        for i in 0..8 {
+            self.h[i] ^= v[i] ^ v[i + 8];
+        }
+    }
+}
+
+/// The mixing function `G`.
+fn mix(v: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize, x: u32, y: u32) {
+    v[a] = v[a].wrapping_add(v[b]).wrapping_add(x);
+    v[d] = (v[d] ^ v[a]).rotate_right(16);
+    v[c] = v[c].wrapping_add(v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(12);
+    v[a] = v[a].wrapping_add(v[b]).wrapping_add(y);
+    v[d] = (v[d] ^ v[a]).rotate_right(8);
+    v[c] = v[c].wrapping_add(v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(7);
+}
+
+/// Computes the 32-byte unkeyed digest of `data`.
+pub fn hash(data: &[u8]) -> [u8; MAX_OUT_LEN] {
+    let mut state = Blake2s::new(MAX_OUT_LEN);
+    state.update(data);
+
+    let mut out = [0u8; MAX_OUT_LEN];
+    state.finalize(&mut out);
+    out
+}
+
+/// Computes the `N`-byte keyed digest of `data`.
+///
+/// # Panics
+///
+/// This function panics if `N` is zero or greater than [`MAX_OUT_LEN`], or if `key` is longer
+/// than [`MAX_KEY_LEN`].
+pub fn keyed_hash<const N: usize>(key: &[u8], data: &[u8]) -> [u8; N] {
+    let mut state = Blake2s::new_keyed(key, N);
+    state.update(data);
+
+    let mut out = [0u8; N];
+    state.finalize(&mut out);
+    out
+}
+
+/// The state of an HMAC-BLAKE2s computation.
+///
+/// Reference: <https://datatracker.ietf.org/doc/html/rfc2104>.
+pub struct Hmac {
+    inner: Blake2s,
+    /// The key XORed with the outer padding
+    outer_key: [u8; BLOCK_LEN],
+}
+
+impl Hmac {
+    /// Starts a computation with `key`.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `key` is longer than [`BLOCK_LEN`].
+    pub fn new(key: &[u8]) -> Self {
+        assert!(key.len() <= BLOCK_LEN);
+
+        let mut inner_key = [0x36u8; BLOCK_LEN];
+        let mut outer_key = [0x5cu8; BLOCK_LEN];
+        for (i, byte) in key.iter().enumerate() {
+            inner_key[i] ^= byte;
+            outer_key[i] ^= byte;
+        }
+
+        let mut inner = Blake2s::new(MAX_OUT_LEN);
+        inner.update(&inner_key);
+
+        Self { inner, outer_key }
+    }
+
+    /// Feeds more data into the computation.
+    pub fn update(&mut self, data: &[u8]) {
+        self.inner.update(data);
+    }
+
+    /// Finishes the computation and returns the MAC.
+    pub fn finalize(self) -> [u8; MAX_OUT_LEN] {
+        let mut inner_digest = [0u8; MAX_OUT_LEN];
+        self.inner.finalize(&mut inner_digest);
+
+        let mut outer = Blake2s::new(MAX_OUT_LEN);
+        outer.update(&self.outer_key);
+        outer.update(&inner_digest);
+
+        let mut out = [0u8; MAX_OUT_LEN];
+        outer.finalize(&mut out);
+        out
+    }
+}
+
+/// Computes the HMAC-BLAKE2s of `data` with `key`.
+pub fn hmac(key: &[u8], data: &[u8]) -> [u8; MAX_OUT_LEN] {
+    let mut state = Hmac::new(key);
+    state.update(data);
+    state.finalize()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::hex;
+
+    #[test]
+    fn test_hash() {
+        // Reference: <https://datatracker.ietf.org/doc/html/rfc7693#appendix-B>.
+        assert_eq!(
+            hash(b"abc"),
+            hex("508c5e8c327c14e2e1a72ba34eeb452f37458b209ed63a294d999b4c86675982")
+        );
+        assert_eq!(
+            hash(b""),
+            hex("69217a3079908094e11121d042354a7c1f55b6482ca1a51e1b250dfd1ed0eef9")
+        );
+    }
+
+    #[test]
+    fn test_keyed_hash() {
+        // Reference: <https://github.com/BLAKE2/BLAKE2/blob/master/testvectors/blake2s-kat.txt>.
+        let key = hex::<32>("000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f");
+        assert_eq!(
+            keyed_hash::<32>(&key, b""),
+            hex("48a8997da407876b3d79c0d92325ad3b89cbb754d86ab71aee047ad345fd2c49")
+        );
+        assert_eq!(
+            keyed_hash::<16>(&key, b""),
+            hex("9536f9b267655743dee97b8a670f9f53")
+        );
+    }
+
+    #[test]
+    fn test_hmac() {
+        let key = hex::<32>("000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f");
+        assert_eq!(
+            hmac(&key, b"The quick brown fox jumps over the lazy dog"),
+            hex("f4660dee21c6a48e2a3d695ceedc26693e37b64b5ac5227476578517ee87fe6d")
+        );
+    }
+
+    #[test]
+    fn test_incremental_update() {
+        let data = [0x5au8; 200];
+
+        let mut state = Blake2s::new(MAX_OUT_LEN);
+        for chunk in data.chunks(7) {
+            state.update(chunk);
+        }
+        let mut out = [0u8; MAX_OUT_LEN];
+        state.finalize(&mut out);
+
+        assert_eq!(out, hash(&data));
+    }
+
+    #[test]
+    fn test_block_boundary() {
+        // A message of exactly one block must be compressed with the finalization flag.
+        let data = [0u8; BLOCK_LEN];
+
+        let mut state = Blake2s::new(MAX_OUT_LEN);
+        state.update(&data[..BLOCK_LEN / 2]);
+        state.update(&data[BLOCK_LEN / 2..]);
+        let mut out = [0u8; MAX_OUT_LEN];
+        state.finalize(&mut out);
+
+        assert_eq!(out, hash(&data));
+        assert_ne!(out, hash(&data[..BLOCK_LEN - 1]));
+    }
+}
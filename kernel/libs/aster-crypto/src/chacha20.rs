@@ -0,0 +1,127 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The ChaCha20 stream cipher.
+//!
+//! This is the IETF variant of ChaCha20, which uses a 96-bit nonce and a 32-bit block counter.
+//!
+//! Reference: <https://datatracker.ietf.org/doc/html/rfc8439#section-2.4>.
+
+/// The length of ChaCha20 keys.
+pub const KEY_LEN: usize = 32;
+/// The length of ChaCha20 nonces.
+pub const NONCE_LEN: usize = 12;
+/// The length of ChaCha20 blocks.
+pub const BLOCK_LEN: usize = 64;
+
+/// The constant words ("expand 32-byte k") at the beginning of the state.
+const CONSTANTS: [u32; 4] = [0x6170_7865, 0x3320_646e, 0x7962_2d32, 0x6b20_6574];
+
+/// Computes the key stream block with the given key, counter, and nonce.
+pub fn block(key: &[u8; KEY_LEN], counter: u32, nonce: &[u8; NONCE_LEN]) -> [u8; BLOCK_LEN] {
+    let mut state = [0u32; 16];
+    state[..4].copy_from_slice(&CONSTANTS);
+    for (word, bytes) in state[4..12].iter_mut().zip(key.chunks_exact(4)) {
+        *word = u32::from_le_bytes(bytes.try_into().unwrap());
+    }
+    state[12] = counter;
+    for (word, bytes) in state[13..].iter_mut().zip(nonce.chunks_exact(4)) {
+        *word = u32::from_le_bytes(bytes.try_into().unwrap());
+    }
+
+    let mut working = state;
+    for _ in 0..10 {
+        // Column rounds
+        quarter_round(&mut working, 0, 4, 8, 12);
+        quarter_round(&mut working, 1, 5, 9, 13);
+        quarter_round(&mut working, 2, 6, 10, 14);
+        quarter_round(&mut working, 3, 7, 11, 15);
+        // Diagonal rounds
+        quarter_round(&mut working, 0, 5, 10, 15);
+        quarter_round(&mut working, 1, 6, 11, 12);
+        quarter_round(&mut working, 2, 7, 8, 13);
+        quarter_round(&mut working, 3, 4, 9, 14);
+    }
+
+    let mut output = [0u8; BLOCK_LEN];
+    for (i, bytes) in output.chunks_exact_mut(4).enumerate() {
+        bytes.copy_from_slice(&working[i].wrapping_add(state[i]).to_le_bytes());
+    }
+    output
+}
+
+fn quarter_round(state: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(16);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(12);
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(8);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(7);
+}
+
+/// Encrypts or decrypts `data` in place, starting from the block `counter`.
+///
+/// # Panics
+///
+/// This function panics if the block counter overflows, i.e., if `data` is longer than
+/// `(2^32 - counter) * 64` bytes.
+pub fn apply_keystream(
+    key: &[u8; KEY_LEN],
+    counter: u32,
+    nonce: &[u8; NONCE_LEN],
+    data: &mut [u8],
+) {
+    for (i, chunk) in data.chunks_mut(BLOCK_LEN).enumerate() {
+        let counter = u32::try_from(i)
+            .ok()
+            .and_then(|i| counter.checked_add(i))
+            .expect("the ChaCha20 block counter overflows");
+        let keystream = block(key, counter, nonce);
+        for (byte, key_byte) in chunk.iter_mut().zip(keystream.iter()) {
+            *byte ^= key_byte;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::hex;
+
+    const KEY: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+
+    #[test]
+    fn test_block() {
+        // Reference: <https://datatracker.ietf.org/doc/html/rfc8439#section-2.3.2>.
+        let key = hex(KEY);
+        let nonce = hex("000000090000004a00000000");
+        let expected = hex::<64>(concat!(
+            "10f1e7e4d13b5915500fdd1fa32071c4c7d1f4c733c068030422aa9ac3d46c4e",
+            "d2826446079faa0914c2d705d98b02a2b5129cd1de164eb9cbd083e8a2503c4e",
+        ));
+
+        assert_eq!(block(&key, 1, &nonce), expected);
+    }
+
+    #[test]
+    fn test_apply_keystream() {
+        // Reference: <https://datatracker.ietf.org/doc/html/rfc8439#section-2.4.2>.
+        let key = hex(KEY);
+        let nonce = hex("000000000000004a00000000");
+        let mut data = *b"Ladies and Gentlemen of the class of '99: If I could offer you \
+            only one tip for the future, sunscreen would be it.";
+        let expected = hex::<114>(concat!(
+            "6e2e359a2568f98041ba0728dd0d6981e97e7aec1d4360c20a27afccfd9fae0b",
+            "f91b65c5524733ab8f593dabcd62b3571639d624e65152ab8f530c359f0861d8",
+            "07ca0dbf500d6a6156a38e088a22b65e52bc514d16ccf806818ce91ab7793736",
+            "5af90bbf74a35be6b40b8eedf2785e42874d",
+        ));
+
+        apply_keystream(&key, 1, &nonce, &mut data);
+        assert_eq!(data, expected);
+
+        apply_keystream(&key, 1, &nonce, &mut data);
+        assert_eq!(&data[..7], b"Ladies ");
+    }
+}
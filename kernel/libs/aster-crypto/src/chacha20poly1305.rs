@@ -0,0 +1,121 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The ChaCha20-Poly1305 AEAD construction.
+//!
+//! Reference: <https://datatracker.ietf.org/doc/html/rfc8439#section-2.8>.
+
+use crate::{
+    chacha20, constant_time_eq,
+    poly1305::{self, Poly1305},
+};
+
+/// The length of ChaCha20-Poly1305 keys.
+pub const KEY_LEN: usize = chacha20::KEY_LEN;
+/// The length of ChaCha20-Poly1305 nonces.
+pub const NONCE_LEN: usize = chacha20::NONCE_LEN;
+/// The length of ChaCha20-Poly1305 tags.
+pub const TAG_LEN: usize = poly1305::TAG_LEN;
+
+/// An error indicating that the ciphertext cannot be authenticated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AuthError;
+
+/// Encrypts `buffer` in place and returns the tag that authenticates both the ciphertext and
+/// the associated data `aad`.
+pub fn seal(
+    key: &[u8; KEY_LEN],
+    nonce: &[u8; NONCE_LEN],
+    aad: &[u8],
+    buffer: &mut [u8],
+) -> [u8; TAG_LEN] {
+    chacha20::apply_keystream(key, 1, nonce, buffer);
+    compute_tag(key, nonce, aad, buffer)
+}
+
+/// Authenticates the ciphertext in `buffer` and the associated data `aad` with `tag`, and then
+/// decrypts `buffer` in place.
+///
+/// If the authentication fails, `buffer` is left unchanged.
+pub fn open(
+    key: &[u8; KEY_LEN],
+    nonce: &[u8; NONCE_LEN],
+    aad: &[u8],
+    buffer: &mut [u8],
+    tag: &[u8; TAG_LEN],
+) -> Result<(), AuthError> {
+    let expected_tag = compute_tag(key, nonce, aad, buffer);
+    if !constant_time_eq(&expected_tag, tag) {
+        return Err(AuthError);
+    }
+
+    chacha20::apply_keystream(key, 1, nonce, buffer);
+    Ok(())
+}
+
+fn compute_tag(
+    key: &[u8; KEY_LEN],
+    nonce: &[u8; NONCE_LEN],
+    aad: &[u8],
+    ciphertext: &[u8],
+) -> [u8; TAG_LEN] {
+    let block = chacha20::block(key, 0, nonce);
+    let mut poly = Poly1305::new(block[..poly1305::KEY_LEN].try_into().unwrap());
+
+    poly.update(aad);
+    poly.pad_to_block();
+    poly.update(ciphertext);
+    poly.pad_to_block();
+    poly.update(&(aad.len() as u64).to_le_bytes());
+    poly.update(&(ciphertext.len() as u64).to_le_bytes());
+
+    poly.finalize()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::hex;
+
+    #[test]
+    fn test_seal_and_open() {
+        // Reference: <https://datatracker.ietf.org/doc/html/rfc8439#section-2.8.2>.
+        let key = hex("808182838485868788898a8b8c8d8e8f909192939495969798999a9b9c9d9e9f");
+        let nonce = hex("070000004041424344454647");
+        let aad = hex::<12>("50515253c0c1c2c3c4c5c6c7");
+        let plaintext = *b"Ladies and Gentlemen of the class of '99: If I could offer you \
+            only one tip for the future, sunscreen would be it.";
+        let expected_ciphertext = hex::<114>(concat!(
+            "d31a8d34648e60db7b86afbc53ef7ec2a4aded51296e08fea9e2b5a736ee62d6",
+            "3dbea45e8ca9671282fafb69da92728b1a71de0a9e060b2905d6a5b67ecd3b36",
+            "92ddbd7f2d778b8c9803aee328091b58fab324e4fad675945585808b4831d7bc",
+            "3ff4def08e4b7a9de576d26586cec64b6116",
+        ));
+        let expected_tag = hex("1ae10b594f09e26a7e902ecbd0600691");
+
+        let mut buffer = plaintext;
+        let tag = seal(&key, &nonce, &aad, &mut buffer);
+        assert_eq!(buffer, expected_ciphertext);
+        assert_eq!(tag, expected_tag);
+
+        let mut tampered = buffer;
+        tampered[0] ^= 1;
+        assert_eq!(
+            open(&key, &nonce, &aad, &mut tampered, &tag),
+            Err(AuthError)
+        );
+        assert_eq!(open(&key, &nonce, &[], &mut buffer, &tag), Err(AuthError));
+
+        assert_eq!(open(&key, &nonce, &aad, &mut buffer, &tag), Ok(()));
+        assert_eq!(buffer, plaintext);
+    }
+
+    #[test]
+    fn test_empty_plaintext() {
+        let key = [0x42u8; KEY_LEN];
+        let nonce = [0u8; NONCE_LEN];
+
+        let tag = seal(&key, &nonce, b"header", &mut []);
+        assert_eq!(open(&key, &nonce, b"header", &mut [], &tag), Ok(()));
+        assert_eq!(open(&key, &nonce, b"footer", &mut [], &tag), Err(AuthError));
+    }
+}
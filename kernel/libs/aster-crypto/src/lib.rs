@@ -0,0 +1,58 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Cryptographic algorithms used by the kernel.
+//!
+//! This crate implements the primitives in pure Rust, so they can be used without any hardware
+//! acceleration:
+//!  - The ChaCha20 stream cipher and the Poly1305 authenticator (see [`chacha20`] and
+//!    [`poly1305`]), which are combined into an AEAD construction (see [`chacha20poly1305`]);
+//!  - The AES block cipher (see [`aes`]);
+//!  - The SHA-2 hash functions (see [`sha2`]);
+//!  - The BLAKE2s hash function, along with its keyed and HMAC variants (see [`blake2s`]);
+//!  - The X25519 Diffie-Hellman function (see [`x25519`]).
+//!
+//! Most users should look up the algorithms by their names with the crypto API (see [`api`]),
+//! which selects the hardware-accelerated implementations if they have been registered.
+//!
+//! Except for AES, the implementations avoid secret-dependent branches and memory accesses,
+//! but they make no attempt to clear the secrets from the memory.
+
+#![no_std]
+#![deny(unsafe_code)]
+
+extern crate alloc;
+
+pub mod aes;
+pub mod api;
+pub mod blake2s;
+pub mod chacha20;
+pub mod chacha20poly1305;
+pub mod poly1305;
+pub mod sha2;
+pub mod x25519;
+
+/// Compares two byte slices in constant time.
+///
+/// The time depends on the lengths of the slices, but not on their contents.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    let diff = a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y));
+    diff == 0
+}
+
+#[cfg(test)]
+mod test_util {
+    /// Decodes a hexadecimal string into a byte array.
+    pub(crate) fn hex<const N: usize>(s: &str) -> [u8; N] {
+        assert_eq!(s.len(), N * 2);
+
+        let mut bytes = [0u8; N];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).unwrap();
+        }
+        bytes
+    }
+}
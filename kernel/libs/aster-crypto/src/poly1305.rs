@@ -0,0 +1,242 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The Poly1305 one-time authenticator.
+//!
+//! The implementation represents the 130-bit accumulator with five 26-bit limbs, which only
+//! needs 32-bit by 32-bit multiplications.
+//!
+//! Reference: <https://datatracker.ietf.org/doc/html/rfc8439#section-2.5>.
+
+/// The length of Poly1305 keys.
+pub const KEY_LEN: usize = 32;
+/// The length of Poly1305 tags.
+pub const TAG_LEN: usize = 16;
+
+const BLOCK_LEN: usize = 16;
+const LIMB_MASK: u32 = 0x3ff_ffff;
+
+/// The state of a Poly1305 computation.
+pub struct Poly1305 {
+    /// The clamped `r` part of the key
+    r: [u32; 5],
+    /// The `s` part of the key
+    pad: [u32; 4],
+    /// The accumulator
+    h: [u32; 5],
+    /// The bytes that do not form a full block yet
+    buffer: [u8; BLOCK_LEN],
+    buffer_len: usize,
+}
+
+impl Poly1305 {
+    /// Starts a computation with a one-time key.
+    pub fn new(key: &[u8; KEY_LEN]) -> Self {
+        let r = [
+            load_le32(&key[0..]) & 0x3ff_ffff,
+            (load_le32(&key[3..]) >> 2) & 0x3ff_ff03,
+            (load_le32(&key[6..]) >> 4) & 0x3ff_c0ff,
+            (load_le32(&key[9..]) >> 6) & 0x3f0_3fff,
+            (load_le32(&key[12..]) >> 8) & 0x00f_ffff,
+        ];
+        let pad = [
+            load_le32(&key[16..]),
+            load_le32(&key[20..]),
+            load_le32(&key[24..]),
+            load_le32(&key[28..]),
+        ];
+
+        Self {
+            r,
+            pad,
+            h: [0; 5],
+            buffer: [0; BLOCK_LEN],
+            buffer_len: 0,
+        }
+    }
+
+    /// Feeds more data into the computation.
+    pub fn update(&mut self, mut data: &[u8]) {
+        if self.buffer_len > 0 {
+            let len = (BLOCK_LEN - self.buffer_len).min(data.len());
+            self.buffer[self.buffer_len..self.buffer_len + len].copy_from_slice(&data[..len]);
+            self.buffer_len += len;
+            data = &data[len..];
+
+            if self.buffer_len < BLOCK_LEN {
+                return;
+            }
+            let block = self.buffer;
+            self.process_block(&block, 1 << 24);
+            self.buffer_len = 0;
+        }
+
+        let mut blocks = data.chunks_exact(BLOCK_LEN);
+        for block in &mut blocks {
+            self.process_block(block.try_into().unwrap(), 1 << 24);
+        }
+
+        let remainder = blocks.remainder();
+        self.buffer[..remainder.len()].copy_from_slice(remainder);
+        self.buffer_len = remainder.len();
+    }
+
+    /// Feeds zeros into the computation until the length of the data is a multiple of 16.
+    ///
+    /// This is used to pad the associated data and the ciphertext in the AEAD construction.
+    pub fn pad_to_block(&mut self) {
+        if self.buffer_len > 0 {
+            self.update(&[0u8; BLOCK_LEN][self.buffer_len..]);
+        }
+    }
+
+    /// Finishes the computation and returns the tag.
+    pub fn finalize(mut self) -> [u8; TAG_LEN] {
+        if self.buffer_len > 0 {
+            let mut block = [0u8; BLOCK_LEN];
+            block[..self.buffer_len].copy_from_slice(&self.buffer[..self.buffer_len]);
+            block[self.buffer_len] = 1;
+            self.process_block(&block, 0);
+        }
+
+        let [mut h0, mut h1, mut h2, mut h3, mut h4] = self.h;
+
+        // Fully carry the accumulator.
+        let mut c = h1 >> 26;
+        h1 &= LIMB_MASK;
+        h2 += c;
+        c = h2 >> 26;
+        h2 &= LIMB_MASK;
+        h3 += c;
+        c = h3 >> 26;
+        h3 &= LIMB_MASK;
+        h4 += c;
+        c = h4 >> 26;
+        h4 &= LIMB_MASK;
+        h0 += c * 5;
+        c = h0 >> 26;
+        h0 &= LIMB_MASK;
+        h1 += c;
+
+        // Compute `h - p = h + 5 - 2^130`.
+        let mut g0 = h0.wrapping_add(5);
+        c = g0 >> 26;
+        g0 &= LIMB_MASK;
+        let mut g1 = h1.wrapping_add(c);
+        c = g1 >> 26;
+        g1 &= LIMB_MASK;
+        let mut g2 = h2.wrapping_add(c);
+        c = g2 >> 26;
+        g2 &= LIMB_MASK;
+        let mut g3 = h3.wrapping_add(c);
+        c = g3 >> 26;
+        g3 &= LIMB_MASK;
+        let g4 = h4.wrapping_add(c).wrapping_sub(1 << 26);
+
+        // Select `h` if `h < p`, or `h - p` otherwise.
+        let mask = (g4 >> 31).wrapping_sub(1);
+        h0 = (h0 & !mask) | (g0 & mask);
+        h1 = (h1 & !mask) | (g1 & mask);
+        h2 = (h2 & !mask) | (g2 & mask);
+        h3 = (h3 & !mask) | (g3 & mask);
+        h4 = (h4 & !mask) | (g4 & mask);
+
+        // Compute `(h + s) mod 2^128`.
+        let words = [
+            h0 | (h1 << 26),
+            (h1 >> 6) | (h2 << 20),
+            (h2 >> 12) | (h3 << 14),
+            (h3 >> 18) | (h4 << 8),
+        ];
+        let mut tag = [0u8; TAG_LEN];
+        let mut carry = 0u64;
+        for (i, bytes) in tag.chunks_exact_mut(4).enumerate() {
+            let f = words[i] as u64 + self.pad[i] as u64 + carry;
+            bytes.copy_from_slice(&(f as u32).to_le_bytes());
+            carry = f >> 32;
+        }
+        tag
+    }
+
+    /// Processes a block, where `hibit` is the bit above the block (i.e., `2^128`) in the limbs.
+    fn process_block(&mut self, block: &[u8; BLOCK_LEN], hibit: u32) {
+        let [r0, r1, r2, r3, r4] = self.r.map(u64::from);
+        let (s1, s2, s3, s4) = (r1 * 5, r2 * 5, r3 * 5, r4 * 5);
+
+        let h0 = (self.h[0] + (load_le32(&block[0..]) & LIMB_MASK)) as u64;
+        let h1 = (self.h[1] + ((load_le32(&block[3..]) >> 2) & LIMB_MASK)) as u64;
+        let h2 = (self.h[2] + ((load_le32(&block[6..]) >> 4) & LIMB_MASK)) as u64;
+        let h3 = (self.h[3] + ((load_le32(&block[9..]) >> 6) & LIMB_MASK)) as u64;
+        let h4 = (self.h[4] + ((load_le32(&block[12..]) >> 8) | hibit)) as u64;
+
+        // Compute `h * r mod p`, where `2^130` is congruent to `5`.
+        let d0 = h0 * r0 + h1 * s4 + h2 * s3 + h3 * s2 + h4 * s1;
+        let mut d1 = h0 * r1 + h1 * r0 + h2 * s4 + h3 * s3 + h4 * s2;
+        let mut d2 = h0 * r2 + h1 * r1 + h2 * r0 + h3 * s4 + h4 * s3;
+        let mut d3 = h0 * r3 + h1 * r2 + h2 * r1 + h3 * r0 + h4 * s4;
+        let mut d4 = h0 * r4 + h1 * r3 + h2 * r2 + h3 * r1 + h4 * r0;
+
+        d1 += d0 >> 26;
+        d2 += d1 >> 26;
+        d3 += d2 >> 26;
+        d4 += d3 >> 26;
+        let mut h0 = (d0 as u32) & LIMB_MASK;
+        let mut h1 = (d1 as u32) & LIMB_MASK;
+        let h2 = (d2 as u32) & LIMB_MASK;
+        let h3 = (d3 as u32) & LIMB_MASK;
+        let h4 = (d4 as u32) & LIMB_MASK;
+
+        h0 += ((d4 >> 26) as u32) * 5;
+        h1 += h0 >> 26;
+        h0 &= LIMB_MASK;
+
+        self.h = [h0, h1, h2, h3, h4];
+    }
+}
+
+fn load_le32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes(bytes[..4].try_into().unwrap())
+}
+
+/// Computes the tag of `data` with a one-time key.
+pub fn mac(key: &[u8; KEY_LEN], data: &[u8]) -> [u8; TAG_LEN] {
+    let mut poly = Poly1305::new(key);
+    poly.update(data);
+    poly.finalize()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::hex;
+
+    #[test]
+    fn test_mac() {
+        // Reference: <https://datatracker.ietf.org/doc/html/rfc8439#section-2.5.2>.
+        let key = hex("85d6be7857556d337f4452fe42d506a80103808afb0db2fd4abff6af4149f51b");
+        let expected = hex("a8061dc1305136c6c22b8baf0c0127a9");
+
+        assert_eq!(mac(&key, b"Cryptographic Forum Research Group"), expected);
+    }
+
+    #[test]
+    fn test_incremental_update() {
+        let key = hex("85d6be7857556d337f4452fe42d506a80103808afb0db2fd4abff6af4149f51b");
+        let message = b"Cryptographic Forum Research Group";
+
+        let mut poly = Poly1305::new(&key);
+        for chunk in message.chunks(5) {
+            poly.update(chunk);
+        }
+        assert_eq!(poly.finalize(), mac(&key, message));
+    }
+
+    #[test]
+    fn test_wrap_around() {
+        // Reference: <https://datatracker.ietf.org/doc/html/rfc8439#appendix-A.3> (test vector #6).
+        let key = hex("0200000000000000000000000000000000000000000000000000000000000000");
+        let message = [0xffu8; 16];
+        let expected = hex("03000000000000000000000000000000");
+
+        assert_eq!(mac(&key, &message), expected);
+    }
+}
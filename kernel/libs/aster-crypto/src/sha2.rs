@@ -0,0 +1,590 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The SHA-2 hash functions (i.e., SHA-224, SHA-256, SHA-384, and SHA-512).
+//!
+//! The compression functions are generic over the `Σ` and `σ` functions (see
+//! [`Sha256Sigma`] and [`Sha512Sigma`]), so that implementations with hardware acceleration
+//! can reuse the rest of the algorithms.
+//!
+//! Reference: <https://nvlpubs.nist.gov/nistpubs/FIPS/NIST.FIPS.180-4.pdf>.
+
+/// The length of SHA-224 digests.
+pub const SHA224_DIGEST_LEN: usize = 28;
+/// The length of SHA-256 digests.
+pub const SHA256_DIGEST_LEN: usize = 32;
+/// The length of SHA-384 digests.
+pub const SHA384_DIGEST_LEN: usize = 48;
+/// The length of SHA-512 digests.
+pub const SHA512_DIGEST_LEN: usize = 64;
+
+/// The length of SHA-224 and SHA-256 blocks.
+pub const SHA256_BLOCK_LEN: usize = 64;
+/// The length of SHA-384 and SHA-512 blocks.
+pub const SHA512_BLOCK_LEN: usize = 128;
+
+/// The `Σ` and `σ` functions of SHA-224 and SHA-256.
+pub trait Sha256Sigma {
+    fn sum0(&self, x: u32) -> u32 {
+        x.rotate_right(2) ^ x.rotate_right(13) ^ x.rotate_right(22)
+    }
+
+    fn sum1(&self, x: u32) -> u32 {
+        x.rotate_right(6) ^ x.rotate_right(11) ^ x.rotate_right(25)
+    }
+
+    fn sig0(&self, x: u32) -> u32 {
+        x.rotate_right(7) ^ x.rotate_right(18) ^ (x >> 3)
+    }
+
+    fn sig1(&self, x: u32) -> u32 {
+        x.rotate_right(17) ^ x.rotate_right(19) ^ (x >> 10)
+    }
+}
+
+/// The `Σ` and `σ` functions of SHA-384 and SHA-512.
+pub trait Sha512Sigma {
+    fn sum0(&self, x: u64) -> u64 {
+        x.rotate_right(28) ^ x.rotate_right(34) ^ x.rotate_right(39)
+    }
+
+    fn sum1(&self, x: u64) -> u64 {
+        x.rotate_right(14) ^ x.rotate_right(18) ^ x.rotate_right(41)
+    }
+
+    fn sig0(&self, x: u64) -> u64 {
+        x.rotate_right(1) ^ x.rotate_right(8) ^ (x >> 7)
+    }
+
+    fn sig1(&self, x: u64) -> u64 {
+        x.rotate_right(19) ^ x.rotate_right(61) ^ (x >> 6)
+    }
+}
+
+/// The software implementation of the `Σ` and `σ` functions.
+pub struct Generic;
+
+impl Sha256Sigma for Generic {}
+impl Sha512Sigma for Generic {}
+
+/// The compression function of SHA-224 and SHA-256.
+pub type Compress256 = fn(&mut [u32; 8], &[u8; SHA256_BLOCK_LEN]);
+/// The compression function of SHA-384 and SHA-512.
+pub type Compress512 = fn(&mut [u64; 8], &[u8; SHA512_BLOCK_LEN]);
+
+const K256: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+const K512: [u64; 80] = [
+    0x428a2f98d728ae22,
+    0x7137449123ef65cd,
+    0xb5c0fbcfec4d3b2f,
+    0xe9b5dba58189dbbc,
+    0x3956c25bf348b538,
+    0x59f111f1b605d019,
+    0x923f82a4af194f9b,
+    0xab1c5ed5da6d8118,
+    0xd807aa98a3030242,
+    0x12835b0145706fbe,
+    0x243185be4ee4b28c,
+    0x550c7dc3d5ffb4e2,
+    0x72be5d74f27b896f,
+    0x80deb1fe3b1696b1,
+    0x9bdc06a725c71235,
+    0xc19bf174cf692694,
+    0xe49b69c19ef14ad2,
+    0xefbe4786384f25e3,
+    0x0fc19dc68b8cd5b5,
+    0x240ca1cc77ac9c65,
+    0x2de92c6f592b0275,
+    0x4a7484aa6ea6e483,
+    0x5cb0a9dcbd41fbd4,
+    0x76f988da831153b5,
+    0x983e5152ee66dfab,
+    0xa831c66d2db43210,
+    0xb00327c898fb213f,
+    0xbf597fc7beef0ee4,
+    0xc6e00bf33da88fc2,
+    0xd5a79147930aa725,
+    0x06ca6351e003826f,
+    0x142929670a0e6e70,
+    0x27b70a8546d22ffc,
+    0x2e1b21385c26c926,
+    0x4d2c6dfc5ac42aed,
+    0x53380d139d95b3df,
+    0x650a73548baf63de,
+    0x766a0abb3c77b2a8,
+    0x81c2c92e47edaee6,
+    0x92722c851482353b,
+    0xa2bfe8a14cf10364,
+    0xa81a664bbc423001,
+    0xc24b8b70d0f89791,
+    0xc76c51a30654be30,
+    0xd192e819d6ef5218,
+    0xd69906245565a910,
+    0xf40e35855771202a,
+    0x106aa07032bbd1b8,
+    0x19a4c116b8d2d0c8,
+    0x1e376c085141ab53,
+    0x2748774cdf8eeb99,
+    0x34b0bcb5e19b48a8,
+    0x391c0cb3c5c95a63,
+    0x4ed8aa4ae3418acb,
+    0x5b9cca4f7763e373,
+    0x682e6ff3d6b2b8a3,
+    0x748f82ee5defb2fc,
+    0x78a5636f43172f60,
+    0x84c87814a1f0ab72,
+    0x8cc702081a6439ec,
+    0x90befffa23631e28,
+    0xa4506cebde82bde9,
+    0xbef9a3f7b2c67915,
+    0xc67178f2e372532b,
+    0xca273eceea26619c,
+    0xd186b8c721c0c207,
+    0xeada7dd6cde0eb1e,
+    0xf57d4f7fee6ed178,
+    0x06f067aa72176fba,
+    0x0a637dc5a2c898a6,
+    0x113f9804bef90dae,
+    0x1b710b35131c471b,
+    0x28db77f523047d84,
+    0x32caab7b40c72493,
+    0x3c9ebe0a15c9bebc,
+    0x431d67c49c100d4c,
+    0x4cc5d4becb3e42b6,
+    0x597f299cfc657e2a,
+    0x5fcb6fab3ad6faec,
+    0x6c44198c4a475817,
+];
+
+const SHA224_IV: [u32; 8] = [
+    0xc1059ed8, 0x367cd507, 0x3070dd17, 0xf70e5939, 0xffc00b31, 0x68581511, 0x64f98fa7, 0xbefa4fa4,
+];
+const SHA256_IV: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+const SHA384_IV: [u64; 8] = [
+    0xcbbb9d5dc1059ed8,
+    0x629a292a367cd507,
+    0x9159015a3070dd17,
+    0x152fecd8f70e5939,
+    0x67332667ffc00b31,
+    0x8eb44a8768581511,
+    0xdb0c2e0d64f98fa7,
+    0x47b5481dbefa4fa4,
+];
+const SHA512_IV: [u64; 8] = [
+    0x6a09e667f3bcc908,
+    0xbb67ae8584caa73b,
+    0x3c6ef372fe94f82b,
+    0xa54ff53a5f1d36f1,
+    0x510e527fade682d1,
+    0x9b05688c2b3e6c1f,
+    0x1f83d9abfb41bd6b,
+    0x5be0cd19137e2179,
+];
+
+/// Compresses a block into the state of SHA-224 or SHA-256 with the software implementation.
+pub fn compress256_generic(state: &mut [u32; 8], block: &[u8; SHA256_BLOCK_LEN]) {
+    compress256(&Generic, state, block);
+}
+
+/// Compresses a block into the state of SHA-384 or SHA-512 with the software implementation.
+pub fn compress512_generic(state: &mut [u64; 8], block: &[u8; SHA512_BLOCK_LEN]) {
+    compress512(&Generic, state, block);
+}
+
+/// Compresses a block into the state of SHA-224 or SHA-256.
+pub fn compress256<S: Sha256Sigma>(
+    sigma: &S,
+    state: &mut [u32; 8],
+    block: &[u8; SHA256_BLOCK_LEN],
+) {
+    let mut w = [0u32; 64];
+    for (word, bytes) in w.iter_mut().zip(block.chunks_exact(4)) {
+        *word = u32::from_be_bytes(bytes.try_into().unwrap());
+    }
+    for i in 16..64 {
+        w[i] = sigma
+            .sig1(w[i - 2])
+            .wrapping_add(w[i - 7])
+            .wrapping_add(sigma.sig0(w[i - 15]))
+            .wrapping_add(w[i - 16]);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+    for i in 0..64 {
+        let t1 = h
+            .wrapping_add(sigma.sum1(e))
+            .wrapping_add((e & f) ^ (!e & g))
+            .wrapping_add(K256[i])
+            .wrapping_add(w[i]);
+        let t2 = sigma.sum0(a).wrapping_add((a & b) ^ (a & c) ^ (b & c));
+        h = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(t1);
+        d = c;
+        c = b;
+        b = a;
+        a = t1.wrapping_add(t2);
+    }
+
+    for (word, value) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+        *word = word.wrapping_add(value);
+    }
+}
+
+/// Compresses a block into the state of SHA-384 or SHA-512.
+pub fn compress512<S: Sha512Sigma>(
+    sigma: &S,
+    state: &mut [u64; 8],
+    block: &[u8; SHA512_BLOCK_LEN],
+) {
+    let mut w = [0u64; 80];
+    for (word, bytes) in w.iter_mut().zip(block.chunks_exact(8)) {
+        *word = u64::from_be_bytes(bytes.try_into().unwrap());
+    }
+    for i in 16..80 {
+        w[i] = sigma
+            .sig1(w[i - 2])
+            .wrapping_add(w[i - 7])
+            .wrapping_add(sigma.sig0(w[i - 15]))
+            .wrapping_add(w[i - 16]);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+    for i in 0..80 {
+        let t1 = h
+            .wrapping_add(sigma.sum1(e))
+            .wrapping_add((e & f) ^ (!e & g))
+            .wrapping_add(K512[i])
+            .wrapping_add(w[i]);
+        let t2 = sigma.sum0(a).wrapping_add((a & b) ^ (a & c) ^ (b & c));
+        h = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(t1);
+        d = c;
+        c = b;
+        b = a;
+        a = t1.wrapping_add(t2);
+    }
+
+    for (word, value) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+        *word = word.wrapping_add(value);
+    }
+}
+
+/// The state of SHA-224 or SHA-256.
+#[derive(Clone)]
+pub struct Sha256 {
+    state: [u32; 8],
+    buffer: [u8; SHA256_BLOCK_LEN],
+    buffer_len: usize,
+    total_len: u64,
+    digest_len: usize,
+    compress: Compress256,
+}
+
+impl Sha256 {
+    /// Creates a SHA-256 state.
+    pub fn new() -> Self {
+        Self::with_compress(SHA256_DIGEST_LEN, compress256_generic)
+    }
+
+    /// Creates a SHA-224 state.
+    pub fn new_224() -> Self {
+        Self::with_compress(SHA224_DIGEST_LEN, compress256_generic)
+    }
+
+    /// Creates a SHA-224 or SHA-256 state, depending on `digest_len`, that compresses the
+    /// blocks with `compress`.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `digest_len` is neither [`SHA224_DIGEST_LEN`] nor
+    /// [`SHA256_DIGEST_LEN`].
+    pub fn with_compress(digest_len: usize, compress: Compress256) -> Self {
+        let state = match digest_len {
+            SHA224_DIGEST_LEN => SHA224_IV,
+            SHA256_DIGEST_LEN => SHA256_IV,
+            _ => panic!("invalid SHA-256 digest length"),
+        };
+
+        Self {
+            state,
+            buffer: [0; SHA256_BLOCK_LEN],
+            buffer_len: 0,
+            total_len: 0,
+            digest_len,
+            compress,
+        }
+    }
+
+    /// Returns the length of the digest.
+    pub fn digest_len(&self) -> usize {
+        self.digest_len
+    }
+
+    /// Absorbs `data` into the state.
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.total_len = self.total_len.wrapping_add(data.len() as u64);
+
+        if self.buffer_len > 0 {
+            let len = (SHA256_BLOCK_LEN - self.buffer_len).min(data.len());
+            self.buffer[self.buffer_len..self.buffer_len + len].copy_from_slice(&data[..len]);
+            self.buffer_len += len;
+            data = &data[len..];
+            if self.buffer_len < SHA256_BLOCK_LEN {
+                return;
+            }
+            (self.compress)(&mut self.state, &self.buffer);
+            self.buffer_len = 0;
+        }
+
+        let mut blocks = data.chunks_exact(SHA256_BLOCK_LEN);
+        for block in blocks.by_ref() {
+            (self.compress)(&mut self.state, block.try_into().unwrap());
+        }
+
+        let remainder = blocks.remainder();
+        self.buffer[..remainder.len()].copy_from_slice(remainder);
+        self.buffer_len = remainder.len();
+    }
+
+    /// Writes the digest to `out`, whose length must be [`Self::digest_len`].
+    pub fn finalize(mut self, out: &mut [u8]) {
+        assert_eq!(out.len(), self.digest_len);
+
+        let bit_len = self.total_len.wrapping_mul(8);
+        self.update(&[0x80]);
+        while self.buffer_len != SHA256_BLOCK_LEN - 8 {
+            self.update(&[0]);
+        }
+        self.update(&bit_len.to_be_bytes());
+        debug_assert_eq!(self.buffer_len, 0);
+
+        for (bytes, word) in out.chunks_mut(4).zip(self.state) {
+            bytes.copy_from_slice(&word.to_be_bytes()[..bytes.len()]);
+        }
+    }
+}
+
+impl Default for Sha256 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The state of SHA-384 or SHA-512.
+#[derive(Clone)]
+pub struct Sha512 {
+    state: [u64; 8],
+    buffer: [u8; SHA512_BLOCK_LEN],
+    buffer_len: usize,
+    total_len: u128,
+    digest_len: usize,
+    compress: Compress512,
+}
+
+impl Sha512 {
+    /// Creates a SHA-512 state.
+    pub fn new() -> Self {
+        Self::with_compress(SHA512_DIGEST_LEN, compress512_generic)
+    }
+
+    /// Creates a SHA-384 state.
+    pub fn new_384() -> Self {
+        Self::with_compress(SHA384_DIGEST_LEN, compress512_generic)
+    }
+
+    /// Creates a SHA-384 or SHA-512 state, depending on `digest_len`, that compresses the
+    /// blocks with `compress`.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `digest_len` is neither [`SHA384_DIGEST_LEN`] nor
+    /// [`SHA512_DIGEST_LEN`].
+    pub fn with_compress(digest_len: usize, compress: Compress512) -> Self {
+        let state = match digest_len {
+            SHA384_DIGEST_LEN => SHA384_IV,
+            SHA512_DIGEST_LEN => SHA512_IV,
+            _ => panic!("invalid SHA-512 digest length"),
+        };
+
+        Self {
+            state,
+            buffer: [0; SHA512_BLOCK_LEN],
+            buffer_len: 0,
+            total_len: 0,
+            digest_len,
+            compress,
+        }
+    }
+
+    /// Returns the length of the digest.
+    pub fn digest_len(&self) -> usize {
+        self.digest_len
+    }
+
+    /// Absorbs `data` into the state.
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.total_len = self.total_len.wrapping_add(data.len() as u128);
+
+        if self.buffer_len > 0 {
+            let len = (SHA512_BLOCK_LEN - self.buffer_len).min(data.len());
+            self.buffer[self.buffer_len..self.buffer_len + len].copy_from_slice(&data[..len]);
+            self.buffer_len += len;
+            data = &data[len..];
+            if self.buffer_len < SHA512_BLOCK_LEN {
+                return;
+            }
+            (self.compress)(&mut self.state, &self.buffer);
+            self.buffer_len = 0;
+        }
+
+        let mut blocks = data.chunks_exact(SHA512_BLOCK_LEN);
+        for block in blocks.by_ref() {
+            (self.compress)(&mut self.state, block.try_into().unwrap());
+        }
+
+        let remainder = blocks.remainder();
+        self.buffer[..remainder.len()].copy_from_slice(remainder);
+        self.buffer_len = remainder.len();
+    }
+
+    /// Writes the digest to `out`, whose length must be [`Self::digest_len`].
+    pub fn finalize(mut self, out: &mut [u8]) {
+        assert_eq!(out.len(), self.digest_len);
+
+        let bit_len = self.total_len.wrapping_mul(8);
+        self.update(&[0x80]);
+        while self.buffer_len != SHA512_BLOCK_LEN - 16 {
+            self.update(&[0]);
+        }
+        self.update(&bit_len.to_be_bytes());
+        debug_assert_eq!(self.buffer_len, 0);
+
+        for (bytes, word) in out.chunks_mut(8).zip(self.state) {
+            bytes.copy_from_slice(&word.to_be_bytes()[..bytes.len()]);
+        }
+    }
+}
+
+impl Default for Sha512 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Computes the SHA-256 digest of `data`.
+pub fn sha256(data: &[u8]) -> [u8; SHA256_DIGEST_LEN] {
+    let mut state = Sha256::new();
+    state.update(data);
+
+    let mut out = [0u8; SHA256_DIGEST_LEN];
+    state.finalize(&mut out);
+    out
+}
+
+/// Computes the SHA-512 digest of `data`.
+pub fn sha512(data: &[u8]) -> [u8; SHA512_DIGEST_LEN] {
+    let mut state = Sha512::new();
+    state.update(data);
+
+    let mut out = [0u8; SHA512_DIGEST_LEN];
+    state.finalize(&mut out);
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::hex;
+
+    const MESSAGE_2: &[u8] = b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq";
+
+    #[test]
+    fn test_sha256() {
+        assert_eq!(
+            sha256(b""),
+            hex("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855")
+        );
+        assert_eq!(
+            sha256(b"abc"),
+            hex("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+        );
+        assert_eq!(
+            sha256(MESSAGE_2),
+            hex("248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1")
+        );
+    }
+
+    #[test]
+    fn test_sha224() {
+        let mut state = Sha256::new_224();
+        state.update(b"abc");
+        let mut out = [0u8; SHA224_DIGEST_LEN];
+        state.finalize(&mut out);
+        assert_eq!(
+            out,
+            hex("23097d223405d8228642a477bda255b32aadbce4bda0b3f7e36c9da7")
+        );
+    }
+
+    #[test]
+    fn test_sha512() {
+        assert_eq!(
+            sha512(b"abc"),
+            hex(concat!(
+                "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a",
+                "2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_sha384() {
+        let mut state = Sha512::new_384();
+        state.update(b"abc");
+        let mut out = [0u8; SHA384_DIGEST_LEN];
+        state.finalize(&mut out);
+        assert_eq!(
+            out,
+            hex(concat!(
+                "cb00753f45a35e8bb5a03d699ac65007272c32ab0eded1631a8b605a43ff5bed",
+                "8086072ba1e7cc2358baeca134c825a7"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_incremental_update() {
+        let data: [u8; 300] = core::array::from_fn(|i| i as u8);
+
+        let mut state = Sha512::new();
+        for chunk in data.chunks(7) {
+            state.update(chunk);
+        }
+        let mut out = [0u8; SHA512_DIGEST_LEN];
+        state.finalize(&mut out);
+        assert_eq!(out, sha512(&data));
+
+        let mut state = Sha256::new();
+        for chunk in data.chunks(13) {
+            state.update(chunk);
+        }
+        let mut out = [0u8; SHA256_DIGEST_LEN];
+        state.finalize(&mut out);
+        assert_eq!(out, sha256(&data));
+    }
+}
@@ -0,0 +1,304 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The X25519 Diffie-Hellman function.
+//!
+//! The field elements modulo `p = 2^255 - 19` are represented with five 51-bit limbs, and the
+//! scalar multiplication is done with the constant-time Montgomery ladder.
+//!
+//! Reference: <https://datatracker.ietf.org/doc/html/rfc7748>.
+
+/// The length of X25519 private keys, public keys, and shared secrets.
+pub const KEY_LEN: usize = 32;
+
+/// The u-coordinate of the base point.
+const BASE_POINT: [u8; KEY_LEN] = {
+    let mut point = [0u8; KEY_LEN];
+    point[0] = 9;
+    point
+};
+
+/// Computes the public key of `private_key`.
+pub fn public_key(private_key: &[u8; KEY_LEN]) -> [u8; KEY_LEN] {
+    x25519(private_key, &BASE_POINT)
+}
+
+/// Computes the shared secret of the private key `scalar` and the public key `point`.
+///
+/// Note that the result is all zeros if `point` is of a small order. Protocols that need
+/// contributory behavior should check this.
+pub fn x25519(scalar: &[u8; KEY_LEN], point: &[u8; KEY_LEN]) -> [u8; KEY_LEN] {
+    let mut k = *scalar;
+    k[0] &= 248;
+    k[31] &= 127;
+    k[31] |= 64;
+
+    let x1 = FieldElement::from_bytes(point);
+    let mut x2 = FieldElement::ONE;
+    let mut z2 = FieldElement::ZERO;
+    let mut x3 = x1;
+    let mut z3 = FieldElement::ONE;
+    let mut swap = 0u64;
+
+    for t in (0..255).rev() {
+        let bit = ((k[t / 8] >> (t % 8)) & 1) as u64;
+        swap ^= bit;
+        FieldElement::conditional_swap(&mut x2, &mut x3, swap);
+        FieldElement::conditional_swap(&mut z2, &mut z3, swap);
+        swap = bit;
+
+        let a = x2.add(&z2);
+        let aa = a.square();
+        let b = x2.sub(&z2);
+        let bb = b.square();
+        let e = aa.sub(&bb);
+        let c = x3.add(&z3);
+        let d = x3.sub(&z3);
+        let da = d.mul(&a);
+        let cb = c.mul(&b);
+        x3 = da.add(&cb).square();
+        z3 = x1.mul(&da.sub(&cb).square());
+        x2 = aa.mul(&bb);
+        z2 = e.mul(&aa.add(&e.mul_small(A24)));
+    }
+    FieldElement::conditional_swap(&mut x2, &mut x3, swap);
+    FieldElement::conditional_swap(&mut z2, &mut z3, swap);
+
+    x2.mul(&z2.invert()).to_bytes()
+}
+
+/// The constant `(A - 2) / 4` of the curve, where `A = 486662`.
+const A24: u64 = 121665;
+
+/// An element of the field modulo `2^255 - 19`.
+///
+/// The limbs are not necessarily reduced, but each of them is well below `2^54`.
+#[derive(Clone, Copy)]
+struct FieldElement([u64; 5]);
+
+const LIMB_MASK: u64 = (1 << 51) - 1;
+
+impl FieldElement {
+    const ZERO: Self = Self([0; 5]);
+    const ONE: Self = Self([1, 0, 0, 0, 0]);
+
+    /// Decodes a field element, ignoring the most significant bit.
+    fn from_bytes(bytes: &[u8; KEY_LEN]) -> Self {
+        let load =
+            |offset: usize| u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap());
+
+        Self([
+            load(0) & LIMB_MASK,
+            (load(6) >> 3) & LIMB_MASK,
+            (load(12) >> 6) & LIMB_MASK,
+            (load(19) >> 1) & LIMB_MASK,
+            (load(24) >> 12) & LIMB_MASK,
+        ])
+    }
+
+    /// Encodes the field element in its canonical form.
+    fn to_bytes(self) -> [u8; KEY_LEN] {
+        let mut l = self.carry().0;
+
+        // Compute the quotient of `(l + 19) / 2^255`, which is one if and only if `l >= p`.
+        let mut q = (l[0] + 19) >> 51;
+        q = (l[1] + q) >> 51;
+        q = (l[2] + q) >> 51;
+        q = (l[3] + q) >> 51;
+        q = (l[4] + q) >> 51;
+
+        // Subtract `q * p` by adding `19 * q` and discarding the bit `2^255`.
+        l[0] += 19 * q;
+        l[1] += l[0] >> 51;
+        l[0] &= LIMB_MASK;
+        l[2] += l[1] >> 51;
+        l[1] &= LIMB_MASK;
+        l[3] += l[2] >> 51;
+        l[2] &= LIMB_MASK;
+        l[4] += l[3] >> 51;
+        l[3] &= LIMB_MASK;
+        l[4] &= LIMB_MASK;
+
+        let words = [
+            l[0] | (l[1] << 51),
+            (l[1] >> 13) | (l[2] << 38),
+            (l[2] >> 26) | (l[3] << 25),
+            (l[3] >> 39) | (l[4] << 12),
+        ];
+        let mut bytes = [0u8; KEY_LEN];
+        for (chunk, word) in bytes.chunks_exact_mut(8).zip(words.iter()) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Propagates the carries so that each limb is below `2^51` (except for a small excess in
+    /// the lowest limb).
+    fn carry(self) -> Self {
+        let mut l = self.0;
+
+        l[1] += l[0] >> 51;
+        l[0] &= LIMB_MASK;
+        l[2] += l[1] >> 51;
+        l[1] &= LIMB_MASK;
+        l[3] += l[2] >> 51;
+        l[2] &= LIMB_MASK;
+        l[4] += l[3] >> 51;
+        l[3] &= LIMB_MASK;
+        l[0] += (l[4] >> 51) * 19;
+        l[4] &= LIMB_MASK;
+
+        Self(l)
+    }
+
+    fn add(&self, other: &Self) -> Self {
+        let mut l = self.0;
+        for (limb, other) in l.iter_mut().zip(other.0.iter()) {
+            *limb += other;
+        }
+        Self(l).carry()
+    }
+
+    fn sub(&self, other: &Self) -> Self {
+        // Add `16 * p` first so that the limbs never underflow.
+        const P16_LOW: u64 = 16 * ((1 << 51) - 19);
+        const P16_HIGH: u64 = 16 * ((1 << 51) - 1);
+
+        let l = [
+            (self.0[0] + P16_LOW) - other.0[0],
+            (self.0[1] + P16_HIGH) - other.0[1],
+            (self.0[2] + P16_HIGH) - other.0[2],
+            (self.0[3] + P16_HIGH) - other.0[3],
+            (self.0[4] + P16_HIGH) - other.0[4],
+        ];
+        Self(l).carry()
+    }
+
+    fn mul(&self, other: &Self) -> Self {
+        let [a0, a1, a2, a3, a4] = self.0.map(u128::from);
+        let [b0, b1, b2, b3, b4] = other.0.map(u128::from);
+        let (b1_19, b2_19, b3_19, b4_19) = (b1 * 19, b2 * 19, b3 * 19, b4 * 19);
+
+        let c0 = a0 * b0 + a4 * b1_19 + a3 * b2_19 + a2 * b3_19 + a1 * b4_19;
+        let mut c1 = a1 * b0 + a0 * b1 + a4 * b2_19 + a3 * b3_19 + a2 * b4_19;
+        let mut c2 = a2 * b0 + a1 * b1 + a0 * b2 + a4 * b3_19 + a3 * b4_19;
+        let mut c3 = a3 * b0 + a2 * b1 + a1 * b2 + a0 * b3 + a4 * b4_19;
+        let mut c4 = a4 * b0 + a3 * b1 + a2 * b2 + a1 * b3 + a0 * b4;
+
+        c1 += c0 >> 51;
+        c2 += c1 >> 51;
+        c3 += c2 >> 51;
+        c4 += c3 >> 51;
+        let mut l = [
+            (c0 as u64) & LIMB_MASK,
+            (c1 as u64) & LIMB_MASK,
+            (c2 as u64) & LIMB_MASK,
+            (c3 as u64) & LIMB_MASK,
+            (c4 as u64) & LIMB_MASK,
+        ];
+        l[0] += ((c4 >> 51) as u64) * 19;
+        l[1] += l[0] >> 51;
+        l[0] &= LIMB_MASK;
+
+        Self(l)
+    }
+
+    fn mul_small(&self, value: u64) -> Self {
+        let mut l = [0u64; 5];
+        let mut carry = 0u128;
+        for (limb, self_limb) in l.iter_mut().zip(self.0.iter()) {
+            let product = (*self_limb as u128) * (value as u128) + carry;
+            *limb = (product as u64) & LIMB_MASK;
+            carry = product >> 51;
+        }
+        l[0] += (carry as u64) * 19;
+
+        Self(l).carry()
+    }
+
+    fn square(&self) -> Self {
+        self.mul(self)
+    }
+
+    fn square_times(&self, times: usize) -> Self {
+        let mut result = *self;
+        for _ in 0..times {
+            result = result.square();
+        }
+        result
+    }
+
+    /// Computes the inverse by raising the element to the power of `p - 2`.
+    fn invert(&self) -> Self {
+        let z2 = self.square();
+        let z9 = z2.square_times(2).mul(self);
+        let z11 = z9.mul(&z2);
+        let z_5_0 = z11.square().mul(&z9);
+        let z_10_0 = z_5_0.square_times(5).mul(&z_5_0);
+        let z_20_0 = z_10_0.square_times(10).mul(&z_10_0);
+        let z_40_0 = z_20_0.square_times(20).mul(&z_20_0);
+        let z_50_0 = z_40_0.square_times(10).mul(&z_10_0);
+        let z_100_0 = z_50_0.square_times(50).mul(&z_50_0);
+        let z_200_0 = z_100_0.square_times(100).mul(&z_100_0);
+        let z_250_0 = z_200_0.square_times(50).mul(&z_50_0);
+
+        z_250_0.square_times(5).mul(&z11)
+    }
+
+    /// Swaps `a` and `b` if `swap` is one, in constant time.
+    fn conditional_swap(a: &mut Self, b: &mut Self, swap: u64) {
+        let mask = 0u64.wrapping_sub(swap);
+        for (a, b) in a.0.iter_mut().zip(b.0.iter_mut()) {
+            let t = mask & (*a ^ *b);
+            *a ^= t;
+            *b ^= t;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::hex;
+
+    #[test]
+    fn test_x25519() {
+        // Reference: <https://datatracker.ietf.org/doc/html/rfc7748#section-5.2>.
+        let scalar = hex("a546e36bf0527c9d3b16154b82465edd62144c0ac1fc5a18506a2244ba449ac4");
+        let point = hex("e6db6867583030db3594c1a424b15f7c726624ec26b3353b10a903a6d0ab1c4c");
+        let expected = hex("c3da55379de9c6908e94ea4df28d084f32eccf03491c71f754b4075577a28552");
+
+        assert_eq!(x25519(&scalar, &point), expected);
+    }
+
+    #[test]
+    fn test_diffie_hellman() {
+        // Reference: <https://datatracker.ietf.org/doc/html/rfc7748#section-6.1>.
+        let alice_private = hex("77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a");
+        let alice_public = hex("8520f0098930a754748b7ddcb43ef75a0dbf3a0d26381af4eba4a98eaa9b4e6a");
+        let bob_private = hex("5dab087e624a8a4b79e17f8b83800ee66f3bb1292618b6fd1c2f8b27ff88e0eb");
+        let bob_public = hex("de9edb7d7b7dc1b4d35b61c2ece435373f8343c85b78674dadfc7e146f882b4f");
+        let shared = hex("4a5d9d5ba4ce2de1728e3bf480350f25e07e21c947d19e3376f09b3c1e161742");
+
+        assert_eq!(public_key(&alice_private), alice_public);
+        assert_eq!(public_key(&bob_private), bob_public);
+        assert_eq!(x25519(&alice_private, &bob_public), shared);
+        assert_eq!(x25519(&bob_private, &alice_public), shared);
+    }
+
+    #[test]
+    fn test_iterations() {
+        // Reference: <https://datatracker.ietf.org/doc/html/rfc7748#section-5.2>.
+        let mut k = BASE_POINT;
+        let mut u = BASE_POINT;
+        for _ in 0..1000 {
+            let result = x25519(&k, &u);
+            u = k;
+            k = result;
+        }
+
+        assert_eq!(
+            k,
+            hex("684cf59ba83309552800ef566f2f4d3c1c3887c49360e3875f2eb94d99532c51")
+        );
+    }
+}
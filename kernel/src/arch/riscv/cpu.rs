@@ -1,21 +1,24 @@
 // SPDX-License-Identifier: MPL-2.0
 
 use ostd::{
-    cpu::context::{CpuExceptionInfo, RawGeneralRegs, UserContext},
+    arch::{boot::DEVICE_TREE, hart},
+    cpu::{
+        context::{CpuExceptionInfo, RawGeneralRegs, UserContext},
+        CpuId,
+    },
     Pod,
 };
 
-use alloc::{
-    format,
-    string::{String, ToString},
-    // vec::Vec,
+use crate::{
+    cpu::{ArchCpuInfo, LinuxAbi},
+    prelude::*,
+    thread::exception::PageFaultInfo,
+    vm::perms::VmPerms,
 };
 
-use crate::{cpu::LinuxAbi, thread::exception::PageFaultInfo, vm::perms::VmPerms};
-
 impl LinuxAbi for UserContext {
     fn syscall_num(&self) -> usize {
-        self.a7()
+        compat_truncate(self, self.a7())
     }
 
     fn syscall_ret(&self) -> usize {
@@ -39,6 +42,7 @@ impl LinuxAbi for UserContext {
             self.a4(),
             self.a5(),
         ]
+        .map(|arg| compat_truncate(self, arg))
     }
 
     fn set_tls_pointer(&mut self, tls: usize) {
@@ -48,6 +52,28 @@ impl LinuxAbi for UserContext {
     fn tls_pointer(&self) -> usize {
         self.tp()
     }
+
+    fn is_compat(&self) -> bool {
+        self.is_compat_mode()
+    }
+
+    fn set_compat(&mut self, is_compat: bool) {
+        self.set_compat_mode(is_compat);
+    }
+}
+
+/// Truncates the value of a register to 32 bits if a 32-bit program is running.
+///
+/// The results of the 32-bit instructions are sign-extended to 64 bits, so the upper bits must
+/// be discarded before the values are used as the unsigned arguments (e.g., pointers). The
+/// signed arguments are still correct after they are truncated again by the system call
+/// handlers.
+fn compat_truncate(user_ctx: &UserContext, reg: usize) -> usize {
+    if user_ctx.is_compat_mode() {
+        reg as u32 as usize
+    } else {
+        reg
+    }
 }
 
 /// General-purpose registers.
@@ -157,201 +183,72 @@ impl TryFrom<&CpuExceptionInfo> for PageFaultInfo {
     }
 }
 
+/// The information of a RISC-V CPU, in the format of Linux.
 pub struct CpuInfo {
-    pub processor: u32,
-    pub vendor_id: String,
-    pub cpu_family: u32,
-    pub model: u32,
-    pub model_name: String,
-    pub stepping: u32,
-    pub microcode: u32,
-    pub cpu_mhz: u32,
-    pub cache_size: u32,      // 以字节为单位
-    pub tlb_size: u32,        // 4K 页数量
-    pub physical_id: u32,
-    pub siblings: u32,
-    pub core_id: u32,
-    pub cpu_cores: u32,
-    pub apicid: u32,
-    pub initial_apicid: u32,
-    pub cpuid_level: u32,
-    pub flags: String,
-    pub bugs: String,
-    pub clflush_size: u8,
-    pub cache_alignment: u32,
-    pub address_sizes: String,
-    pub power_management: String,
+    processor: u32,
+    hart: usize,
+    isa: String,
+    mmu: &'static str,
+    mvendorid: usize,
+    marchid: usize,
+    mimpid: usize,
 }
 
-impl CpuInfo {
-    pub fn new(processor_id: u32) -> Self {
+impl ArchCpuInfo for CpuInfo {
+    fn new(cpu: CpuId) -> Self {
+        let hart = hart::hart_id(cpu);
+        let machine_ids = hart::machine_ids();
+
+        // The device tree does not describe the harts if ACPI is used.
+        let device_tree = DEVICE_TREE.get().unwrap();
+        let cpu_node = device_tree.find_node("/cpus").and_then(|_| {
+            device_tree
+                .cpus()
+                .find(|cpu_node| cpu_node.ids().first() == hart)
+        });
+        // The ISA string is built from the single-letter extensions if the device tree only
+        // lists the extensions, or if the harts are not in the device tree.
+        let isa = cpu_node
+            .as_ref()
+            .and_then(|cpu_node| cpu_node.property("riscv,isa"))
+            .and_then(|prop| prop.as_str())
+            .map(str::to_string)
+            .unwrap_or_else(|| {
+                let letters = (b'a'..=b'z')
+                    .filter(|letter| ostd::arch::isa::has_extension(*letter))
+                    .map(char::from)
+                    .collect::<String>();
+                alloc::format!("rv64{}", letters)
+            });
+
         Self {
-            processor: processor_id,
-            vendor_id: Self::get_vendor_id(),
-            cpu_family: Self::get_cpu_family(),
-            model: Self::get_model(),
-            model_name: Self::get_model_name(),
-            stepping: Self::get_stepping(),
-            microcode: Self::get_microcode(),
-            cpu_mhz: Self::get_clock_speed().unwrap_or(0),
-            cache_size: Self::get_cache_size().unwrap_or(0),
-            tlb_size: Self::get_tlb_size().unwrap_or(0),
-            physical_id: Self::get_physical_id().unwrap_or(0),
-            siblings: Self::get_siblings_count().unwrap_or(0),
-            core_id: Self::get_core_id(),
-            cpu_cores: Self::get_cpu_cores(),
-            apicid: Self::get_apicid(),
-            initial_apicid: Self::get_initial_apicid(),
-            cpuid_level: Self::get_cpuid_level(),
-            flags: Self::get_cpu_flags(),
-            bugs: Self::get_cpu_bugs(),
-            clflush_size: Self::get_clflush_size(),
-            cache_alignment: Self::get_cache_alignment(),
-            address_sizes: Self::get_address_sizes(),
-            power_management: Self::get_power_management(),
+            processor: cpu.as_usize() as u32,
+            hart,
+            isa,
+            // The kernel always uses Sv48, whatever the CPU supports.
+            mmu: "sv48",
+            mvendorid: machine_ids.mvendorid,
+            marchid: machine_ids.marchid,
+            mimpid: machine_ids.mimpid,
         }
     }
 
-    /// 将 CPU 信息格式化成字符串
-    pub fn collect_cpu_info(&self) -> String {
-        format!(
-            "processor\t: {}\n\
-             vendor_id\t: {}\n\
-             cpu family\t: {}\n\
-             model\t\t: {}\n\
-             model name\t: {}\n\
-             stepping\t: {}\n\
-             microcode\t: 0x{:x}\n\
-             cpu MHz\t\t: {}\n\
-             cache size\t: {} KB\n\
-             TLB size\t: {} 4K pages\n\
-             physical id\t: {}\n\
-             siblings\t: {}\n\
-             core id\t\t: {}\n\
-             cpu cores\t: {}\n\
-             apicid\t\t: {}\n\
-             initial apicid\t: {}\n\
-             cpuid level\t: {}\n\
-             flags\t\t: {}\n\
-             bugs\t\t: {}\n\
-             clflush size\t: {} bytes\n\
-             cache_alignment\t: {} bytes\n\
-             address sizes\t: {}\n\
-             power management: {}\n",
-            self.processor,
-            self.vendor_id,
-            self.cpu_family,
-            self.model,
-            self.model_name,
-            self.stepping,
-            self.microcode,
-            self.cpu_mhz,
-            self.cache_size / 1024, // 输出为 KB
-            self.tlb_size,
-            self.physical_id,
-            self.siblings,
-            self.core_id,
-            self.cpu_cores,
-            self.apicid,
-            self.initial_apicid,
-            self.cpuid_level,
-            self.flags,
-            self.bugs,
-            self.clflush_size,
-            self.cache_alignment,
-            self.address_sizes,
-            self.power_management
-        )
-    }
-
-    fn get_vendor_id() -> String {
-        "riscv".to_string()
-    }
-
-    fn get_cpu_family() -> u32 {
-        0
-    }
-
-    fn get_model() -> u32 {
-        0
-    }
-
-    fn get_stepping() -> u32 {
-        0
-    }
-
-    fn get_model_name() -> String {
-        "RISC-V".to_string()
-    }
-
-    fn get_microcode() -> u32 {
-        0
-    }
-
-    fn get_clock_speed() -> Option<u32> {
-        // 返回默认 1000 MHz
-        Some(1000)
-    }
-
-    /// 返回缓存大小（字节）
-    fn get_cache_size() -> Option<u32> {
-        // 默认 32 MB（32 * 1024 * 1024 字节）
-        Some(32 * 1024 * 1024)
-    }
-
-    fn get_tlb_size() -> Option<u32> {
-        Some(512)
-    }
-
-    fn get_physical_id() -> Option<u32> {
-        Some(0)
-    }
-
-    fn get_siblings_count() -> Option<u32> {
-        Some(1)
-    }
-
-    fn get_core_id() -> u32 {
-        0
-    }
-
-    fn get_cpu_cores() -> u32 {
-        1
-    }
-
-    fn get_apicid() -> u32 {
-        0
-    }
-
-    fn get_initial_apicid() -> u32 {
-        Self::get_apicid()
-    }
-
-    fn get_cpuid_level() -> u32 {
-        0
-    }
-
-    fn get_cpu_flags() -> String {
-        "fpu vme de pse tsc msr pae mce".to_string()
-    }
-
-    fn get_cpu_bugs() -> String {
-        "".to_string()
-    }
-
-    fn get_clflush_size() -> u8 {
-        64
-    }
-
-    fn get_cache_alignment() -> u32 {
-        64
-    }
-
-    fn get_address_sizes() -> String {
-        "64 bits physical, 64 bits virtual".to_string()
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("processor", self.processor.to_string()),
+            ("hart", self.hart.to_string()),
+            ("isa", self.isa.clone()),
+            ("mmu", self.mmu.to_string()),
+            ("mvendorid", alloc::format!("{:#x}", self.mvendorid)),
+            ("marchid", alloc::format!("{:#x}", self.marchid)),
+            ("mimpid", alloc::format!("{:#x}", self.mimpid)),
+        ]
     }
+}
 
-    fn get_power_management() -> String {
-        "".to_string()
-    }
-}
\ No newline at end of file
+/// Returns the hardware capabilities that are reported to the user space as `AT_HWCAP`.
+///
+/// On RISC-V, this is the bitmap of the single-letter ISA extensions.
+pub fn hwcap() -> u64 {
+    ostd::arch::isa::hwcap()
+}
@@ -3,15 +3,23 @@
 use alloc::{
     format,
     string::{String, ToString},
+    vec,
     vec::Vec,
 };
 
 use ostd::{
-    cpu::context::{cpuid, CpuException, CpuExceptionInfo, RawGeneralRegs, UserContext},
+    cpu::{
+        context::{cpuid, CpuException, CpuExceptionInfo, RawGeneralRegs, UserContext},
+        CpuId,
+    },
     Pod,
 };
 
-use crate::{cpu::LinuxAbi, thread::exception::PageFaultInfo, vm::perms::VmPerms};
+use crate::{
+    cpu::{ArchCpuInfo, LinuxAbi},
+    thread::exception::PageFaultInfo,
+    vm::perms::VmPerms,
+};
 
 impl LinuxAbi for UserContext {
     fn syscall_num(&self) -> usize {
@@ -48,6 +56,15 @@ impl LinuxAbi for UserContext {
     fn tls_pointer(&self) -> usize {
         self.fsbase()
     }
+
+    fn is_compat(&self) -> bool {
+        false
+    }
+
+    fn set_compat(&mut self, is_compat: bool) {
+        // The 32-bit programs are rejected when they are loaded.
+        debug_assert!(!is_compat);
+    }
 }
 
 /// General-purpose registers.
@@ -171,10 +188,10 @@ pub struct CpuInfo {
     power_management: String,
 }
 
-impl CpuInfo {
-    pub fn new(processor_id: u32) -> Self {
+impl ArchCpuInfo for CpuInfo {
+    fn new(cpu: CpuId) -> Self {
         Self {
-            processor: processor_id,
+            processor: cpu.as_usize() as u32,
             vendor_id: Self::get_vendor_id(),
             cpu_family: Self::get_cpu_family(),
             model: Self::get_model(),
@@ -201,58 +218,36 @@ impl CpuInfo {
         }
     }
 
-    /// Collect and format CPU information into a `String`
-    pub fn collect_cpu_info(&self) -> String {
-        format!(
-            "processor\t: {}\n\
-             vendor_id\t: {}\n\
-             cpu family\t: {}\n\
-             model\t\t: {}\n\
-             model name\t: {}\n\
-             stepping\t: {}\n\
-             microcode\t: 0x{:x}\n\
-             cpu MHz\t\t: {}\n\
-             cache size\t: {} KB\n\
-             TLB size\t: {} 4K pages\n\
-             physical id\t: {}\n\
-             siblings\t: {}\n\
-             core id\t\t: {}\n\
-             cpu cores\t: {}\n\
-             apicid\t\t: {}\n\
-             initial apicid\t: {}\n\
-             cpuid level\t: {}\n\
-             flags\t\t: {}\n\
-             bugs\t\t: {}\n\
-             clflush size\t: {} bytes\n\
-             cache_alignment\t: {} bytes\n\
-             address sizes\t: {}\n\
-             power management: {}\n",
-            self.processor,
-            self.vendor_id,
-            self.cpu_family,
-            self.model,
-            self.model_name,
-            self.stepping,
-            self.microcode,
-            self.cpu_mhz,
-            self.cache_size / 1024,
-            self.tlb_size,
-            self.physical_id,
-            self.siblings,
-            self.core_id,
-            self.cpu_cores,
-            self.apicid,
-            self.initial_apicid,
-            self.cpuid_level,
-            self.flags,
-            self.bugs,
-            self.clflush_size,
-            self.cache_alignment,
-            self.address_sizes,
-            self.power_management
-        )
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("processor", self.processor.to_string()),
+            ("vendor_id", self.vendor_id.clone()),
+            ("cpu family", self.cpu_family.to_string()),
+            ("model", self.model.to_string()),
+            ("model name", self.model_name.clone()),
+            ("stepping", self.stepping.to_string()),
+            ("microcode", format!("0x{:x}", self.microcode)),
+            ("cpu MHz", self.cpu_mhz.to_string()),
+            ("cache size", format!("{} KB", self.cache_size / 1024)),
+            ("TLB size", format!("{} 4K pages", self.tlb_size)),
+            ("physical id", self.physical_id.to_string()),
+            ("siblings", self.siblings.to_string()),
+            ("core id", self.core_id.to_string()),
+            ("cpu cores", self.cpu_cores.to_string()),
+            ("apicid", self.apicid.to_string()),
+            ("initial apicid", self.initial_apicid.to_string()),
+            ("cpuid level", self.cpuid_level.to_string()),
+            ("flags", self.flags.clone()),
+            ("bugs", self.bugs.clone()),
+            ("clflush size", format!("{} bytes", self.clflush_size)),
+            ("cache_alignment", format!("{} bytes", self.cache_alignment)),
+            ("address sizes", self.address_sizes.clone()),
+            ("power management", self.power_management.clone()),
+        ]
     }
+}
 
+impl CpuInfo {
     fn get_vendor_id() -> String {
         let cpuid = cpuid::CpuId::new();
         cpuid.get_vendor_info().unwrap().to_string()
@@ -524,3 +519,10 @@ impl CpuInfo {
         " ".to_string()
     }
 }
+
+/// Returns the hardware capabilities that are reported to the user space as `AT_HWCAP`.
+///
+/// On x86-64, this is the value of EDX returned by CPUID leaf 1.
+pub fn hwcap() -> u64 {
+    cpuid::cpuid!(1).edx as u64
+}
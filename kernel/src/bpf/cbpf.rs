@@ -0,0 +1,252 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The conversion of the classic BPF programs to the eBPF programs.
+//!
+//! Like Linux, the classic BPF programs (i.e., the seccomp filters) are not interpreted
+//! directly. Instead, they are converted to the eBPF programs. The accumulator `A` and the
+//! index register `X` are mapped to R0 and R7, the context is saved in R6, and the scratch
+//! memory `M[]` resides at the top of the stack.
+//!
+//! Since the classic BPF programs only load from their contexts with the absolute offsets,
+//! the loads of the bytes and the half words, the indirect loads and the `MSH` loads are not
+//! supported.
+//!
+//! Reference: <https://www.kernel.org/doc/html/latest/networking/filter.html>
+
+use super::insn::*;
+use crate::prelude::*;
+
+/// A classic BPF instruction, i.e., `struct sock_filter`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub struct SockFilter {
+    pub code: u16,
+    pub jt: u8,
+    pub jf: u8,
+    pub k: u32,
+}
+
+/// The maximum number of the instructions in a classic BPF program.
+pub const BPF_MAXINSNS: usize = 4096;
+
+/// The number of the words in the scratch memory.
+const BPF_MEMWORDS: u32 = 16;
+
+// The classic BPF instructions that are not ALU or conditional jump instructions.
+const LD_W_ABS: u8 = BPF_LD | BPF_W | 0x20;
+const LD_W_LEN: u8 = BPF_LD | BPF_W | 0x80;
+const LDX_W_LEN: u8 = BPF_LDX | BPF_W | 0x80;
+const LD_IMM: u8 = BPF_LD | BPF_IMM;
+const LDX_IMM: u8 = BPF_LDX | BPF_IMM;
+const LD_MEM: u8 = BPF_LD | BPF_MEM;
+const LDX_MEM: u8 = BPF_LDX | BPF_MEM;
+const ST: u8 = BPF_ST;
+const STX: u8 = BPF_STX;
+const JA: u8 = BPF_JMP | BPF_JA;
+const RET_K: u8 = 0x06;
+const RET_A: u8 = 0x16;
+const TAX: u8 = 0x07;
+const TXA: u8 = 0x87;
+
+const REG_A: u8 = 0;
+const REG_CTX: u8 = 6;
+const REG_X: u8 = 7;
+
+/// The instructions that start a converted program, which save the context and clear `A`
+/// and `X`.
+const PROLOGUE: [BpfInsn; 3] = [
+    BpfInsn::new(BPF_ALU64 | BPF_MOV | BPF_X, REG_CTX, 1, 0, 0),
+    BpfInsn::new(BPF_ALU | BPF_MOV | BPF_K, REG_A, 0, 0, 0),
+    BpfInsn::new(BPF_ALU | BPF_MOV | BPF_K, REG_X, 0, 0, 0),
+];
+
+/// Converts a classic BPF program whose context is of the length.
+///
+/// The instructions are checked as Linux does, e.g., the jumps must be in bounds, and the
+/// program must end with a return instruction.
+pub fn convert(filter: &[SockFilter], ctx_len: u32) -> Result<Vec<BpfInsn>> {
+    if filter.is_empty() || filter.len() > BPF_MAXINSNS {
+        return_errno_with_message!(Errno::EINVAL, "invalid length of the classic BPF program");
+    }
+    if !matches!(filter.last().unwrap().code as u8, RET_K | RET_A) {
+        return_errno_with_message!(Errno::EINVAL, "the classic BPF program does not return");
+    }
+
+    // The indexes of the first converted instructions.
+    let mut starts = Vec::with_capacity(filter.len());
+    let mut nr_insns = PROLOGUE.len();
+    for insn in filter.iter() {
+        starts.push(nr_insns);
+        nr_insns += converted_len(insn, ctx_len)?;
+    }
+
+    let mut insns = Vec::with_capacity(nr_insns);
+    insns.extend_from_slice(&PROLOGUE);
+    for (index, insn) in filter.iter().enumerate() {
+        // Returns the offset of the jump at the position to the target of the classic jump.
+        let jump_off = |pos: usize, delta: u32| -> Result<i16> {
+            let target = (index + 1)
+                .checked_add(delta as usize)
+                .and_then(|target| starts.get(target))
+                .ok_or_else(|| {
+                    Error::with_message(Errno::EINVAL, "the classic jump is out of bounds")
+                })?;
+            Ok((*target - (pos + 1)) as i16)
+        };
+        let pos = insns.len();
+        let code = insn.code as u8;
+        let k = insn.k as i32;
+
+        match code {
+            // A = *(u32 *)(ctx + k)
+            LD_W_ABS => insns.push(BpfInsn::new(
+                BPF_LDX | BPF_MEM | BPF_W,
+                REG_A,
+                REG_CTX,
+                insn.k as i16,
+                0,
+            )),
+            LD_W_LEN => insns.push(mov_imm(REG_A, ctx_len as i32)),
+            LDX_W_LEN => insns.push(mov_imm(REG_X, ctx_len as i32)),
+            LD_IMM => insns.push(mov_imm(REG_A, k)),
+            LDX_IMM => insns.push(mov_imm(REG_X, k)),
+            LD_MEM => insns.push(load_mem(REG_A, insn.k)),
+            LDX_MEM => insns.push(load_mem(REG_X, insn.k)),
+            ST => insns.push(store_mem(REG_A, insn.k)),
+            STX => insns.push(store_mem(REG_X, insn.k)),
+            JA => insns.push(BpfInsn::new(
+                BPF_JMP | BPF_JA,
+                0,
+                0,
+                jump_off(pos, insn.k)?,
+                0,
+            )),
+            RET_K => {
+                insns.push(mov_imm(REG_A, k));
+                insns.push(exit());
+            }
+            RET_A => insns.push(exit()),
+            TAX => insns.push(BpfInsn::new(BPF_ALU | BPF_MOV | BPF_X, REG_X, REG_A, 0, 0)),
+            TXA => insns.push(BpfInsn::new(BPF_ALU | BPF_MOV | BPF_X, REG_A, REG_X, 0, 0)),
+            _ if code & 0x07 == BPF_ALU => {
+                let op = code & 0xf0;
+                if code & BPF_X != 0 {
+                    if matches!(op, BPF_DIV | BPF_MOD) {
+                        // Like Linux, the program returns zero if `X` is zero.
+                        insns.push(BpfInsn::new(BPF_JMP32 | BPF_JNE | BPF_K, REG_X, 0, 2, 0));
+                        insns.push(mov_imm(REG_A, 0));
+                        insns.push(exit());
+                    }
+                    insns.push(BpfInsn::new(BPF_ALU | op | BPF_X, REG_A, REG_X, 0, 0));
+                } else {
+                    insns.push(BpfInsn::new(BPF_ALU | op | BPF_K, REG_A, 0, 0, k));
+                }
+            }
+            _ => {
+                // The conditional jumps compare the 32-bit `A` with `X` or the unsigned `k`.
+                let (src, imm) = if code & BPF_X != 0 {
+                    (REG_X, 0)
+                } else {
+                    (0, k)
+                };
+                insns.push(BpfInsn::new(
+                    BPF_JMP32 | (code & 0xf8),
+                    REG_A,
+                    src,
+                    jump_off(pos, insn.jt as u32)?,
+                    imm,
+                ));
+                insns.push(BpfInsn::new(
+                    BPF_JMP | BPF_JA,
+                    0,
+                    0,
+                    jump_off(pos + 1, insn.jf as u32)?,
+                    0,
+                ));
+            }
+        }
+        debug_assert_eq!(insns.len() - pos, converted_len(insn, ctx_len).unwrap());
+    }
+
+    Ok(insns)
+}
+
+/// Checks a classic BPF instruction, and returns the number of the converted instructions.
+fn converted_len(insn: &SockFilter, ctx_len: u32) -> Result<usize> {
+    let Ok(code) = u8::try_from(insn.code) else {
+        return_errno_with_message!(Errno::EINVAL, "unknown classic BPF instruction");
+    };
+
+    let len = match code {
+        LD_W_ABS => {
+            if insn.k % 4 != 0 || insn.k.checked_add(4).is_none_or(|end| end > ctx_len) {
+                return_errno_with_message!(Errno::EINVAL, "the load is out of the context");
+            }
+            1
+        }
+        LD_MEM | LDX_MEM | ST | STX => {
+            if insn.k >= BPF_MEMWORDS {
+                return_errno_with_message!(Errno::EINVAL, "the scratch memory is out of bounds");
+            }
+            1
+        }
+        LD_W_LEN | LDX_W_LEN | LD_IMM | LDX_IMM | JA | RET_A | TAX | TXA => 1,
+        RET_K => 2,
+        _ if code & 0x07 == BPF_ALU => {
+            let is_src_x = code & BPF_X != 0;
+            match code & 0xf0 {
+                BPF_ADD | BPF_SUB | BPF_MUL | BPF_OR | BPF_AND | BPF_XOR => 1,
+                BPF_NEG if !is_src_x => 1,
+                BPF_DIV | BPF_MOD if !is_src_x && insn.k == 0 => {
+                    return_errno_with_message!(Errno::EINVAL, "division by zero");
+                }
+                BPF_DIV | BPF_MOD if is_src_x => 4,
+                BPF_DIV | BPF_MOD => 1,
+                BPF_LSH | BPF_RSH if !is_src_x && insn.k >= 32 => {
+                    return_errno_with_message!(Errno::EINVAL, "invalid shift");
+                }
+                BPF_LSH | BPF_RSH => 1,
+                _ => return_errno_with_message!(Errno::EINVAL, "unknown ALU instruction"),
+            }
+        }
+        _ if code & 0x07 == BPF_JMP => match code & 0xf0 {
+            BPF_JEQ | BPF_JGT | BPF_JGE | BPF_JSET => 2,
+            _ => return_errno_with_message!(Errno::EINVAL, "unknown jump instruction"),
+        },
+        _ => return_errno_with_message!(Errno::EINVAL, "unsupported classic BPF instruction"),
+    };
+    Ok(len)
+}
+
+fn mov_imm(reg: u8, imm: i32) -> BpfInsn {
+    BpfInsn::new(BPF_ALU | BPF_MOV | BPF_K, reg, 0, 0, imm)
+}
+
+fn load_mem(reg: u8, k: u32) -> BpfInsn {
+    BpfInsn::new(
+        BPF_LDX | BPF_MEM | BPF_W,
+        reg,
+        FRAME_POINTER as u8,
+        mem_off(k),
+        0,
+    )
+}
+
+fn store_mem(reg: u8, k: u32) -> BpfInsn {
+    BpfInsn::new(
+        BPF_STX | BPF_MEM | BPF_W,
+        FRAME_POINTER as u8,
+        reg,
+        mem_off(k),
+        0,
+    )
+}
+
+fn exit() -> BpfInsn {
+    BpfInsn::new(BPF_JMP | BPF_EXIT, 0, 0, 0, 0)
+}
+
+/// Returns the offset of `M[k]` from the frame pointer.
+fn mem_off(k: u32) -> i16 {
+    -(((BPF_MEMWORDS - k) * 4) as i16)
+}
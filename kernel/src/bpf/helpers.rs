@@ -0,0 +1,133 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The helper functions that can be called by the eBPF programs.
+//!
+//! The helper functions may be called in any context (e.g., in interrupt handlers), so they
+//! never sleep. The failures are reported to the programs as negative error numbers, except
+//! that the invalid memory accesses abort the programs.
+
+use ostd::{cpu::PinCurrentCpu, task::disable_preempt};
+
+use super::{map::BpfUpdateFlag, vm::Vm};
+use crate::{
+    prelude::*,
+    process::posix_thread::{AsPosixThread, PosixThread},
+    thread::Thread,
+    time::clocks::MonotonicClock,
+};
+
+/// The helper functions.
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromInt)]
+pub enum BpfHelper {
+    MapLookupElem = 1,
+    MapUpdateElem = 2,
+    MapDeleteElem = 3,
+    KtimeGetNs = 5,
+    GetSmpProcessorId = 8,
+    GetCurrentPidTgid = 14,
+    GetCurrentUidGid = 15,
+    GetCurrentComm = 16,
+}
+
+impl BpfHelper {
+    /// Returns the number of the arguments, which are passed in R1 to R5.
+    pub fn nr_args(&self) -> usize {
+        match self {
+            Self::MapLookupElem | Self::MapDeleteElem | Self::GetCurrentComm => 2,
+            Self::MapUpdateElem => 4,
+            Self::KtimeGetNs
+            | Self::GetSmpProcessorId
+            | Self::GetCurrentPidTgid
+            | Self::GetCurrentUidGid => 0,
+        }
+    }
+
+    /// Calls the helper function with the arguments in the registers of the program.
+    pub fn call(&self, vm: &mut Vm) -> Result<u64> {
+        let ret = match self {
+            Self::MapLookupElem => {
+                let map = vm.map(vm.arg(1))?;
+                let key = read_vec(vm, vm.arg(2), map.key_size())?;
+                match map.lookup(&key) {
+                    Some(value) => vm.expose_map_value(value),
+                    None => 0,
+                }
+            }
+            Self::MapUpdateElem => {
+                let map = vm.map(vm.arg(1))?;
+                let key = read_vec(vm, vm.arg(2), map.key_size())?;
+                let value = read_vec(vm, vm.arg(3), map.value_size())?;
+                let result = BpfUpdateFlag::try_from(vm.arg(4))
+                    .map_err(Error::from)
+                    .and_then(|flag| map.update(&key, &value, flag));
+                errno_or_zero(result)
+            }
+            Self::MapDeleteElem => {
+                let map = vm.map(vm.arg(1))?;
+                let key = read_vec(vm, vm.arg(2), map.key_size())?;
+                errno_or_zero(map.delete(&key))
+            }
+            Self::KtimeGetNs => MonotonicClock::get().read_time().as_nanos() as u64,
+            Self::GetSmpProcessorId => disable_preempt().current_cpu().as_usize() as u64,
+            Self::GetCurrentPidTgid => with_current_posix_thread(|posix_thread| {
+                ((posix_thread.process().pid() as u64) << 32) | posix_thread.tid() as u64
+            }),
+            Self::GetCurrentUidGid => with_current_posix_thread(|posix_thread| {
+                let credentials = posix_thread.credentials();
+                let uid: u32 = credentials.ruid().into();
+                let gid: u32 = credentials.rgid().into();
+                ((gid as u64) << 32) | uid as u64
+            }),
+            Self::GetCurrentComm => {
+                let (addr, size) = (vm.arg(1), vm.arg(2) as usize);
+                if size == 0 || size > PAGE_SIZE {
+                    return Ok(errno(Errno::EINVAL));
+                }
+                // Like Linux, the name is truncated to fit in the buffer with the null byte.
+                let mut comm = vec![0u8; size];
+                let name = Thread::current().and_then(|thread| {
+                    let posix_thread = thread.as_posix_thread()?;
+                    let thread_name = posix_thread.thread_name().try_lock()?;
+                    let name = thread_name.as_ref()?.name().ok()??.to_bytes().to_vec();
+                    Some(name)
+                });
+                let Some(name) = name else {
+                    vm.write_mem(addr, &comm)?;
+                    return Ok(errno(Errno::EINVAL));
+                };
+                let len = name.len().min(size - 1);
+                comm[..len].copy_from_slice(&name[..len]);
+                vm.write_mem(addr, &comm)?;
+                0
+            }
+        };
+        Ok(ret)
+    }
+}
+
+fn read_vec(vm: &Vm, addr: u64, len: usize) -> Result<Vec<u8>> {
+    let mut buf = vec![0u8; len];
+    vm.read_mem(addr, &mut buf)?;
+    Ok(buf)
+}
+
+/// Calls the closure with the POSIX thread that is running, or returns zero if there is none
+/// (e.g., the kernel threads).
+fn with_current_posix_thread(f: impl FnOnce(&PosixThread) -> u64) -> u64 {
+    Thread::current()
+        .as_ref()
+        .and_then(|thread| thread.as_posix_thread())
+        .map_or(0, f)
+}
+
+fn errno(errno: Errno) -> u64 {
+    -(errno as i64) as u64
+}
+
+fn errno_or_zero(result: Result<()>) -> u64 {
+    match result {
+        Ok(()) => 0,
+        Err(err) => errno(err.error()),
+    }
+}
@@ -0,0 +1,129 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The encoding of the eBPF instructions.
+//!
+//! Reference: <https://www.kernel.org/doc/html/latest/bpf/standardization/instruction-set.html>
+
+use crate::prelude::*;
+
+/// An eBPF instruction.
+///
+/// The 64-bit immediate loads take two instructions, where the second one carries the high
+/// 32 bits of the immediate.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub struct BpfInsn {
+    pub code: u8,
+    /// The destination register in the low 4 bits, and the source register in the high 4 bits.
+    pub regs: u8,
+    pub off: i16,
+    pub imm: i32,
+}
+
+impl BpfInsn {
+    pub const fn new(code: u8, dst: u8, src: u8, off: i16, imm: i32) -> Self {
+        Self {
+            code,
+            regs: (src << 4) | (dst & 0xf),
+            off,
+            imm,
+        }
+    }
+
+    pub fn dst(&self) -> usize {
+        (self.regs & 0xf) as usize
+    }
+
+    pub fn src(&self) -> usize {
+        (self.regs >> 4) as usize
+    }
+
+    pub fn class(&self) -> u8 {
+        self.code & 0x07
+    }
+
+    /// Returns the operation of the ALU or jump instructions.
+    pub fn op(&self) -> u8 {
+        self.code & 0xf0
+    }
+
+    /// Returns whether the source operand of the ALU or jump instructions is a register.
+    pub fn is_src_reg(&self) -> bool {
+        self.code & BPF_X != 0
+    }
+
+    /// Returns the size of the load or store instructions.
+    pub fn size(&self) -> u8 {
+        self.code & 0x18
+    }
+
+    /// Returns the mode of the load or store instructions.
+    pub fn mode(&self) -> u8 {
+        self.code & 0xe0
+    }
+}
+
+// The instruction classes.
+pub const BPF_LD: u8 = 0x00;
+pub const BPF_LDX: u8 = 0x01;
+pub const BPF_ST: u8 = 0x02;
+pub const BPF_STX: u8 = 0x03;
+pub const BPF_ALU: u8 = 0x04;
+pub const BPF_JMP: u8 = 0x05;
+pub const BPF_JMP32: u8 = 0x06;
+pub const BPF_ALU64: u8 = 0x07;
+
+// The sizes of the load and store instructions.
+pub const BPF_W: u8 = 0x00;
+pub const BPF_H: u8 = 0x08;
+pub const BPF_B: u8 = 0x10;
+pub const BPF_DW: u8 = 0x18;
+
+// The modes of the load and store instructions.
+pub const BPF_IMM: u8 = 0x00;
+pub const BPF_MEM: u8 = 0x60;
+
+// The sources of the ALU and jump instructions.
+pub const BPF_K: u8 = 0x00;
+pub const BPF_X: u8 = 0x08;
+
+// The operations of the ALU instructions.
+pub const BPF_ADD: u8 = 0x00;
+pub const BPF_SUB: u8 = 0x10;
+pub const BPF_MUL: u8 = 0x20;
+pub const BPF_DIV: u8 = 0x30;
+pub const BPF_OR: u8 = 0x40;
+pub const BPF_AND: u8 = 0x50;
+pub const BPF_LSH: u8 = 0x60;
+pub const BPF_RSH: u8 = 0x70;
+pub const BPF_NEG: u8 = 0x80;
+pub const BPF_MOD: u8 = 0x90;
+pub const BPF_XOR: u8 = 0xa0;
+pub const BPF_MOV: u8 = 0xb0;
+pub const BPF_ARSH: u8 = 0xc0;
+pub const BPF_END: u8 = 0xd0;
+
+// The operations of the jump instructions.
+pub const BPF_JA: u8 = 0x00;
+pub const BPF_JEQ: u8 = 0x10;
+pub const BPF_JGT: u8 = 0x20;
+pub const BPF_JGE: u8 = 0x30;
+pub const BPF_JSET: u8 = 0x40;
+pub const BPF_JNE: u8 = 0x50;
+pub const BPF_JSGT: u8 = 0x60;
+pub const BPF_JSGE: u8 = 0x70;
+pub const BPF_CALL: u8 = 0x80;
+pub const BPF_EXIT: u8 = 0x90;
+pub const BPF_JLT: u8 = 0xa0;
+pub const BPF_JLE: u8 = 0xb0;
+pub const BPF_JSLT: u8 = 0xc0;
+pub const BPF_JSLE: u8 = 0xd0;
+
+/// The source register of a 64-bit immediate load that loads a map by its file descriptor.
+pub const BPF_PSEUDO_MAP_FD: usize = 1;
+
+/// The number of the registers, i.e., R0 to R10.
+pub const NR_REGS: usize = 11;
+
+/// The read-only frame pointer, i.e., R10.
+pub const FRAME_POINTER: usize = 10;
@@ -0,0 +1,106 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use ostd::trace::{Tracepoint, TracepointProbe, MAX_NR_ARGS};
+
+use super::{
+    anon_metadata,
+    prog::{BpfProg, BpfProgType},
+};
+use crate::{
+    events::IoEvents,
+    fs::{file_handle::FileLike, utils::Metadata},
+    prelude::*,
+    process::signal::{PollHandle, Pollable},
+};
+
+/// An attachment of an eBPF program, which is detached once the link is dropped.
+///
+/// The link is referred to by a file descriptor in the user space, so closing the file
+/// detaches the program.
+pub struct BpfLink {
+    target: LinkTarget,
+}
+
+enum LinkTarget {
+    Tracepoint {
+        tracepoint: &'static Tracepoint,
+        probe: TracepointProbe,
+    },
+    #[cfg(target_arch = "riscv64")]
+    Kprobe { addr: Vaddr },
+}
+
+impl BpfLink {
+    /// Attaches the program to the tracepoint.
+    ///
+    /// The context of the program is the arguments of the tracepoint, which are all 64-bit.
+    pub fn attach_tracepoint(prog: Arc<BpfProg>, tracepoint: &'static Tracepoint) -> Result<Self> {
+        if prog.type_() != BpfProgType::RawTracepoint {
+            return_errno_with_message!(Errno::EINVAL, "the program is not for tracepoints");
+        }
+
+        let probe: TracepointProbe = Arc::new(move |args: &[u64; MAX_NR_ARGS]| {
+            let mut ctx = [0u8; MAX_NR_ARGS * 8];
+            for (bytes, arg) in ctx.chunks_exact_mut(8).zip(args) {
+                bytes.copy_from_slice(&arg.to_ne_bytes());
+            }
+            // The return values of the tracing programs are ignored.
+            let _ = prog.run(&ctx);
+        });
+        tracepoint.attach_probe(probe.clone());
+
+        Ok(Self {
+            target: LinkTarget::Tracepoint { tracepoint, probe },
+        })
+    }
+
+    /// Attaches the program to a new kprobe at the address.
+    ///
+    /// The context of the program is the registers in the layout of `struct pt_regs`, i.e.,
+    /// the program counter followed by the general registers from `ra` to `t6`.
+    #[cfg(target_arch = "riscv64")]
+    pub fn attach_kprobe(prog: Arc<BpfProg>, addr: Vaddr) -> Result<Self> {
+        use ostd::arch::kprobe::{register_kprobe, Kprobe};
+
+        if prog.type_() != BpfProgType::Kprobe {
+            return_errno_with_message!(Errno::EINVAL, "the program is not for kprobes");
+        }
+
+        let kprobe = Kprobe::new(addr).pre_handler(move |trap_frame| {
+            let mut ctx = trap_frame.general;
+            ctx.zero = trap_frame.sepc;
+            let _ = prog.run(ctx.as_bytes());
+        });
+        register_kprobe(kprobe)?;
+
+        Ok(Self {
+            target: LinkTarget::Kprobe { addr },
+        })
+    }
+}
+
+impl Drop for BpfLink {
+    fn drop(&mut self) {
+        match &self.target {
+            LinkTarget::Tracepoint { tracepoint, probe } => {
+                tracepoint.detach_probe(probe);
+            }
+            #[cfg(target_arch = "riscv64")]
+            LinkTarget::Kprobe { addr } => {
+                ostd::arch::kprobe::unregister_kprobe(*addr);
+            }
+        }
+    }
+}
+
+impl Pollable for BpfLink {
+    fn poll(&self, _mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        IoEvents::empty()
+    }
+}
+
+impl FileLike for BpfLink {
+    fn metadata(&self) -> Metadata {
+        anon_metadata()
+    }
+}
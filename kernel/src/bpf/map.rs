@@ -0,0 +1,219 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::mem::size_of;
+
+use ostd::sync::LocalIrqDisabled;
+
+use super::anon_metadata;
+use crate::{
+    events::IoEvents,
+    fs::{file_handle::FileLike, utils::Metadata},
+    prelude::*,
+    process::signal::{PollHandle, Pollable},
+};
+
+/// The types of the eBPF maps.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromInt)]
+pub enum BpfMapType {
+    Hash = 1,
+    Array = 2,
+}
+
+/// How an element is updated.
+#[repr(u64)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromInt)]
+pub enum BpfUpdateFlag {
+    /// Creates a new element or updates an existing one.
+    Any = 0,
+    /// Creates a new element only if it does not exist.
+    NoExist = 1,
+    /// Updates an existing element.
+    Exist = 2,
+}
+
+/// The maximum size of a key or a value.
+const MAX_ELEM_SIZE: usize = PAGE_SIZE;
+
+/// The maximum total size of the values in a map.
+const MAX_MAP_SIZE: usize = 16 * 1024 * 1024;
+
+/// The value of an element in an eBPF map.
+///
+/// The value is shared by the programs and the user space, which may access it concurrently.
+/// Each access is atomic with respect to the others.
+pub struct BpfMapValue(SpinLock<Box<[u8]>, LocalIrqDisabled>);
+
+impl BpfMapValue {
+    fn new(value: &[u8]) -> Self {
+        Self(SpinLock::new(value.into()))
+    }
+
+    /// Reads the bytes at the offset.
+    ///
+    /// Returns `None` if the bytes are out of bounds.
+    pub fn read(&self, offset: usize, buf: &mut [u8]) -> Option<()> {
+        let value = self.0.lock();
+        buf.copy_from_slice(value.get(offset..offset.checked_add(buf.len())?)?);
+        Some(())
+    }
+
+    /// Writes the bytes at the offset.
+    ///
+    /// Returns `None` if the bytes are out of bounds.
+    pub fn write(&self, offset: usize, buf: &[u8]) -> Option<()> {
+        let mut value = self.0.lock();
+        value
+            .get_mut(offset..offset.checked_add(buf.len())?)?
+            .copy_from_slice(buf);
+        Some(())
+    }
+}
+
+/// An eBPF map, i.e., a key-value store shared by the eBPF programs and the user space.
+///
+/// The map is referred to by a file descriptor in the user space.
+pub struct BpfMap {
+    type_: BpfMapType,
+    key_size: usize,
+    value_size: usize,
+    max_entries: usize,
+    elems: SpinLock<BTreeMap<Vec<u8>, Arc<BpfMapValue>>, LocalIrqDisabled>,
+}
+
+impl BpfMap {
+    /// Creates a map.
+    ///
+    /// The elements of an array map are created with zeros, and their keys are the 32-bit
+    /// indexes.
+    pub fn new(
+        type_: BpfMapType,
+        key_size: usize,
+        value_size: usize,
+        max_entries: usize,
+    ) -> Result<Self> {
+        if key_size == 0 || value_size == 0 || max_entries == 0 {
+            return_errno_with_message!(Errno::EINVAL, "the map is empty");
+        }
+        if key_size > MAX_ELEM_SIZE || value_size > MAX_ELEM_SIZE {
+            return_errno_with_message!(Errno::E2BIG, "the key or the value is too big");
+        }
+        if value_size
+            .checked_mul(max_entries)
+            .is_none_or(|size| size > MAX_MAP_SIZE)
+        {
+            return_errno_with_message!(Errno::ENOMEM, "the map is too big");
+        }
+
+        let mut elems = BTreeMap::new();
+        if type_ == BpfMapType::Array {
+            if key_size != size_of::<u32>() {
+                return_errno_with_message!(Errno::EINVAL, "the keys of arrays must be 32-bit");
+            }
+            let zeros = vec![0u8; value_size];
+            for index in 0..max_entries as u32 {
+                elems.insert(
+                    index.to_ne_bytes().to_vec(),
+                    Arc::new(BpfMapValue::new(&zeros)),
+                );
+            }
+        }
+
+        Ok(Self {
+            type_,
+            key_size,
+            value_size,
+            max_entries,
+            elems: SpinLock::new(elems),
+        })
+    }
+
+    pub fn key_size(&self) -> usize {
+        self.key_size
+    }
+
+    pub fn value_size(&self) -> usize {
+        self.value_size
+    }
+
+    /// Looks up the value of the key.
+    pub fn lookup(&self, key: &[u8]) -> Option<Arc<BpfMapValue>> {
+        self.elems.lock().get(key).cloned()
+    }
+
+    /// Updates the value of the key.
+    pub fn update(&self, key: &[u8], value: &[u8], flag: BpfUpdateFlag) -> Result<()> {
+        debug_assert_eq!(key.len(), self.key_size);
+        debug_assert_eq!(value.len(), self.value_size);
+
+        let mut elems = self.elems.lock();
+        match (elems.get(key), flag) {
+            (Some(_), BpfUpdateFlag::NoExist) => {
+                return_errno_with_message!(Errno::EEXIST, "the element already exists")
+            }
+            (Some(elem), _) => {
+                elem.write(0, value).unwrap();
+            }
+            (None, BpfUpdateFlag::Exist) => {
+                return_errno_with_message!(Errno::ENOENT, "the element does not exist")
+            }
+            (None, _) if self.type_ == BpfMapType::Array => {
+                return_errno_with_message!(Errno::E2BIG, "the index is out of bounds")
+            }
+            (None, _) => {
+                if elems.len() >= self.max_entries {
+                    return_errno_with_message!(Errno::E2BIG, "the map is full");
+                }
+                elems.insert(key.to_vec(), Arc::new(BpfMapValue::new(value)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Deletes the element of the key.
+    pub fn delete(&self, key: &[u8]) -> Result<()> {
+        if self.type_ == BpfMapType::Array {
+            return_errno_with_message!(Errno::EINVAL, "the elements of arrays cannot be deleted");
+        }
+        if self.elems.lock().remove(key).is_none() {
+            return_errno_with_message!(Errno::ENOENT, "the element does not exist");
+        }
+        Ok(())
+    }
+
+    /// Returns the key after the given key, or the first key if the given key is `None` or
+    /// does not exist.
+    pub fn next_key(&self, key: Option<&[u8]>) -> Option<Vec<u8>> {
+        if self.type_ == BpfMapType::Array {
+            // The keys of arrays are iterated in the order of the indexes, which is not the
+            // order of their bytes.
+            let next_index = key
+                .and_then(|key| Some(u32::from_ne_bytes(key.try_into().ok()?)))
+                .filter(|index| (*index as usize) < self.max_entries)
+                .map_or(0, |index| index + 1);
+            return ((next_index as usize) < self.max_entries)
+                .then(|| next_index.to_ne_bytes().to_vec());
+        }
+
+        let elems = self.elems.lock();
+        let next = match key {
+            Some(key) if elems.contains_key(key) => elems
+                .range::<[u8], _>((core::ops::Bound::Excluded(key), core::ops::Bound::Unbounded))
+                .next(),
+            _ => elems.iter().next(),
+        };
+        next.map(|(key, _)| key.clone())
+    }
+}
+
+impl Pollable for BpfMap {
+    fn poll(&self, _mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        IoEvents::empty()
+    }
+}
+
+impl FileLike for BpfMap {
+    fn metadata(&self) -> Metadata {
+        anon_metadata()
+    }
+}
@@ -0,0 +1,64 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! A lightweight eBPF virtual machine.
+//!
+//! The eBPF programs are loaded from the user space by the `bpf` system call. Each program is
+//! checked by the verifier (see [`verifier`]) before it is accepted, and then runs in the
+//! interpreter (see [`vm`]) when it is triggered. The programs can share data with each other
+//! and with the user space in the maps (see [`BpfMap`]), and can call a few helper functions,
+//! e.g., to get the time or the current process.
+//!
+//! The programs can be attached to the tracepoints (see [`ostd::trace`]) and the kprobes, and
+//! the seccomp filters, which are classic BPF programs, are converted to eBPF programs and run
+//! in the same interpreter (see [`cbpf`]).
+//!
+//! Compared to Linux, only a few types of programs, maps and helper functions are supported,
+//! the programs cannot contain loops or call each other, and the programs are never compiled
+//! to the native code.
+//!
+//! Reference: <https://www.kernel.org/doc/html/latest/bpf/index.html>
+
+pub mod cbpf;
+mod helpers;
+pub mod insn;
+mod link;
+mod map;
+mod prog;
+mod verifier;
+mod vm;
+
+pub use link::BpfLink;
+pub use map::{BpfMap, BpfMapType, BpfUpdateFlag};
+pub use prog::{BpfProg, BpfProgType};
+pub use verifier::{VerifierError, MAX_NR_INSNS};
+
+use crate::{
+    fs::utils::{InodeMode, InodeType, Metadata},
+    prelude::*,
+    process::{Gid, Uid},
+    time::clocks::RealTimeClock,
+};
+
+/// Returns the metadata of the files that refer to the eBPF objects.
+fn anon_metadata() -> Metadata {
+    // This is a dummy implementation.
+    // TODO: Add "anonymous inode fs" and link the eBPF objects to it.
+    let now = RealTimeClock::get().read_time();
+    Metadata {
+        dev: 0,
+        ino: 0,
+        size: 0,
+        blk_size: 0,
+        blocks: 0,
+        atime: now,
+        mtime: now,
+        ctime: now,
+        btime: None,
+        type_: InodeType::File,
+        mode: InodeMode::from_bits_truncate(0o600),
+        nlinks: 1,
+        uid: Uid::new_root(),
+        gid: Gid::new_root(),
+        rdev: 0,
+    }
+}
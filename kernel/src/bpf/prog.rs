@@ -0,0 +1,108 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{
+    anon_metadata,
+    helpers::BpfHelper,
+    insn::BpfInsn,
+    map::BpfMap,
+    verifier::{self, VerifierError},
+    vm::Vm,
+};
+use crate::{
+    events::IoEvents,
+    fs::{file_handle::FileLike, utils::Metadata},
+    prelude::*,
+    process::signal::{PollHandle, Pollable},
+};
+
+/// The types of the eBPF programs, which determine their contexts and where they can be
+/// attached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BpfProgType {
+    /// The programs attached to the kprobes, whose contexts are the registers.
+    Kprobe,
+    /// The programs attached to the tracepoints, whose contexts are the arguments of the
+    /// tracepoints.
+    RawTracepoint,
+    /// The seccomp filters, which are converted from the classic BPF and whose contexts are
+    /// the system calls.
+    ///
+    /// Like Linux, the programs of the type cannot be loaded from the user space directly.
+    Seccomp,
+}
+
+impl BpfProgType {
+    /// Returns the type of the programs loaded from the user space.
+    pub fn from_user(prog_type: u32) -> Result<Self> {
+        match prog_type {
+            BPF_PROG_TYPE_KPROBE => Ok(Self::Kprobe),
+            BPF_PROG_TYPE_RAW_TRACEPOINT => Ok(Self::RawTracepoint),
+            _ => return_errno_with_message!(Errno::EINVAL, "unsupported program type"),
+        }
+    }
+
+    /// Returns whether the programs of the type can call the helper function.
+    pub(super) fn can_call(&self, _helper: BpfHelper) -> bool {
+        match self {
+            Self::Kprobe | Self::RawTracepoint => true,
+            Self::Seccomp => false,
+        }
+    }
+}
+
+const BPF_PROG_TYPE_KPROBE: u32 = 2;
+const BPF_PROG_TYPE_RAW_TRACEPOINT: u32 = 17;
+
+/// A verified eBPF program.
+///
+/// The program is referred to by a file descriptor in the user space.
+pub struct BpfProg {
+    type_: BpfProgType,
+    insns: Vec<BpfInsn>,
+    maps: Vec<Arc<BpfMap>>,
+}
+
+impl BpfProg {
+    /// Verifies the instructions and creates a program.
+    ///
+    /// The maps used by the program are resolved by their file descriptors.
+    pub fn new(
+        type_: BpfProgType,
+        mut insns: Vec<BpfInsn>,
+        resolve_map: impl FnMut(i32) -> Option<Arc<BpfMap>>,
+    ) -> core::result::Result<Self, VerifierError> {
+        let maps = verifier::verify(type_, &mut insns, resolve_map)?;
+        Ok(Self { type_, insns, maps })
+    }
+
+    pub fn type_(&self) -> BpfProgType {
+        self.type_
+    }
+
+    pub(super) fn insns(&self) -> &[BpfInsn] {
+        &self.insns
+    }
+
+    pub(super) fn maps(&self) -> &[Arc<BpfMap>] {
+        &self.maps
+    }
+
+    /// Runs the program with the context, and returns the value in R0.
+    ///
+    /// It fails if the program accesses the memory out of its bounds.
+    pub fn run(&self, ctx: &[u8]) -> Result<u64> {
+        Vm::new(self, ctx).run()
+    }
+}
+
+impl Pollable for BpfProg {
+    fn poll(&self, _mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        IoEvents::empty()
+    }
+}
+
+impl FileLike for BpfProg {
+    fn metadata(&self) -> Metadata {
+        anon_metadata()
+    }
+}
@@ -0,0 +1,299 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The verifier of the eBPF programs.
+//!
+//! The verifier checks that every instruction is valid, and that a program always terminates
+//! without reading the uninitialized registers. Like Linux, the instructions that cannot be
+//! reached are rejected, except in the converted seccomp filters. Unlike Linux, only the
+//! forward jumps are allowed, i.e., the bounded loops are rejected, so every instruction is
+//! executed at most once in each run.
+//!
+//! The types of the values in the registers are not tracked. Instead, the programs can only
+//! access their memory through the tagged addresses, which are checked by the interpreter
+//! when the programs run (see [`super::vm`]).
+
+use core::fmt;
+
+use super::{helpers::BpfHelper, insn::*, map::BpfMap, prog::BpfProgType};
+use crate::prelude::*;
+
+/// The maximum number of the instructions in a program.
+pub const MAX_NR_INSNS: usize = 65536;
+
+/// The registers that are initialized when a program starts, i.e., the context in R1 and
+/// the frame pointer.
+const INITIAL_REGS: u16 = (1 << 1) | (1 << FRAME_POINTER);
+
+/// The registers that are clobbered by the helper functions, i.e., R1 to R5.
+const CALLER_SAVED_REGS: u16 = 0b11_1110;
+
+/// The reason why a program is rejected.
+#[derive(Debug, Clone, Copy)]
+pub struct VerifierError {
+    insn: usize,
+    errno: Errno,
+    msg: &'static str,
+}
+
+impl VerifierError {
+    fn new(insn: usize, msg: &'static str) -> Self {
+        Self {
+            insn,
+            errno: Errno::EINVAL,
+            msg,
+        }
+    }
+}
+
+impl fmt::Display for VerifierError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "insn {}: {}", self.insn, self.msg)
+    }
+}
+
+impl From<VerifierError> for Error {
+    fn from(err: VerifierError) -> Self {
+        Error::with_message(err.errno, err.msg)
+    }
+}
+
+/// Verifies the instructions of a program.
+///
+/// The 64-bit immediate loads of the maps are rewritten to refer to the indexes of the maps,
+/// which are resolved by their file descriptors and returned.
+pub fn verify(
+    type_: BpfProgType,
+    insns: &mut [BpfInsn],
+    mut resolve_map: impl FnMut(i32) -> Option<Arc<BpfMap>>,
+) -> core::result::Result<Vec<Arc<BpfMap>>, VerifierError> {
+    if insns.is_empty() {
+        return Err(VerifierError::new(0, "the program is empty"));
+    }
+    if insns.len() > MAX_NR_INSNS {
+        return Err(VerifierError {
+            insn: 0,
+            errno: Errno::E2BIG,
+            msg: "the program is too long",
+        });
+    }
+
+    let nr_insns = insns.len();
+    let mut maps: Vec<Arc<BpfMap>> = Vec::new();
+    // The registers that are initialized on all the paths to each instruction, or `None` if
+    // the instruction has not been reached.
+    let mut states: Vec<Option<u16>> = vec![None; nr_insns];
+    states[0] = Some(INITIAL_REGS);
+
+    let mut index = 0;
+    while index < nr_insns {
+        let Some(init_regs) = states[index] else {
+            // The classic BPF programs may contain the unreachable instructions, which are
+            // never executed.
+            if type_ == BpfProgType::Seccomp {
+                index += 1;
+                continue;
+            }
+            return Err(VerifierError::new(index, "unreachable instruction"));
+        };
+        let error = |msg| VerifierError::new(index, msg);
+        let insn = insns[index];
+        let check_read = |reg: usize| {
+            if init_regs & (1 << reg) == 0 {
+                return Err(error("reading an uninitialized register"));
+            }
+            Ok(())
+        };
+        let check_write = |reg: usize| {
+            if reg == FRAME_POINTER {
+                return Err(error("writing the read-only frame pointer"));
+            }
+            Ok(())
+        };
+
+        if insn.dst() >= NR_REGS || insn.src() >= NR_REGS {
+            return Err(error("invalid register"));
+        }
+
+        let mut next_regs = init_regs;
+        let mut jump_target = None;
+        let mut falls_through = true;
+        let mut width = 1;
+
+        match insn.class() {
+            class @ (BPF_ALU | BPF_ALU64) => {
+                let bits = if class == BPF_ALU64 { 64 } else { 32 };
+                if insn.off != 0 {
+                    return Err(error("unsupported signed ALU operation"));
+                }
+                if !insn.is_src_reg() && insn.src() != 0 {
+                    return Err(error("reserved fields are not zeros"));
+                }
+                match insn.op() {
+                    BPF_END => {
+                        if class == BPF_ALU64 {
+                            return Err(error("unsupported byte swap"));
+                        }
+                        if !matches!(insn.imm, 16 | 32 | 64) {
+                            return Err(error("invalid width of byte swap"));
+                        }
+                        check_read(insn.dst())?;
+                    }
+                    BPF_NEG => {
+                        if insn.is_src_reg() || insn.imm != 0 {
+                            return Err(error("reserved fields are not zeros"));
+                        }
+                        check_read(insn.dst())?;
+                    }
+                    BPF_MOV => {
+                        if insn.is_src_reg() {
+                            check_read(insn.src())?;
+                        }
+                    }
+                    op @ (BPF_ADD | BPF_SUB | BPF_MUL | BPF_DIV | BPF_OR | BPF_AND | BPF_LSH
+                    | BPF_RSH | BPF_MOD | BPF_XOR | BPF_ARSH) => {
+                        if !insn.is_src_reg() {
+                            if matches!(op, BPF_DIV | BPF_MOD) && insn.imm == 0 {
+                                return Err(error("division by zero"));
+                            }
+                            if matches!(op, BPF_LSH | BPF_RSH | BPF_ARSH) && insn.imm as u32 >= bits
+                            {
+                                return Err(error("invalid shift"));
+                            }
+                        } else {
+                            check_read(insn.src())?;
+                        }
+                        check_read(insn.dst())?;
+                    }
+                    _ => return Err(error("unknown ALU operation")),
+                }
+                check_write(insn.dst())?;
+                next_regs |= 1 << insn.dst();
+            }
+            class @ (BPF_JMP | BPF_JMP32) => match insn.op() {
+                BPF_JA => {
+                    if class == BPF_JMP32 || insn.is_src_reg() || insn.regs != 0 || insn.imm != 0 {
+                        return Err(error("invalid jump"));
+                    }
+                    jump_target = Some(index as isize + 1 + insn.off as isize);
+                    falls_through = false;
+                }
+                BPF_CALL => {
+                    if class == BPF_JMP32 || insn.is_src_reg() || insn.regs != 0 || insn.off != 0 {
+                        return Err(error("only the helper functions can be called"));
+                    }
+                    let helper = BpfHelper::try_from(insn.imm)
+                        .ok()
+                        .filter(|helper| type_.can_call(*helper))
+                        .ok_or_else(|| error("unknown helper function"))?;
+                    for reg in 1..=helper.nr_args() {
+                        check_read(reg)?;
+                    }
+                    next_regs = (init_regs & !CALLER_SAVED_REGS) | 1;
+                }
+                BPF_EXIT => {
+                    if class == BPF_JMP32 || insn.is_src_reg() || insn.regs != 0 || insn.imm != 0 {
+                        return Err(error("invalid exit"));
+                    }
+                    check_read(0)?;
+                    falls_through = false;
+                }
+                BPF_JEQ | BPF_JGT | BPF_JGE | BPF_JSET | BPF_JNE | BPF_JSGT | BPF_JSGE
+                | BPF_JLT | BPF_JLE | BPF_JSLT | BPF_JSLE => {
+                    if !insn.is_src_reg() && insn.src() != 0 {
+                        return Err(error("reserved fields are not zeros"));
+                    }
+                    check_read(insn.dst())?;
+                    if insn.is_src_reg() {
+                        check_read(insn.src())?;
+                    }
+                    jump_target = Some(index as isize + 1 + insn.off as isize);
+                }
+                _ => return Err(error("unknown jump operation")),
+            },
+            BPF_LD => {
+                if insn.code != BPF_LD | BPF_IMM | BPF_DW {
+                    return Err(error("unsupported load"));
+                }
+                let Some(next) = insns.get(index + 1) else {
+                    return Err(error("incomplete 64-bit immediate load"));
+                };
+                if next.code != 0 || next.regs != 0 || next.off != 0 || insn.off != 0 {
+                    return Err(error("reserved fields are not zeros"));
+                }
+                match insn.src() {
+                    0 => (),
+                    BPF_PSEUDO_MAP_FD => {
+                        let map = resolve_map(insn.imm).ok_or(VerifierError {
+                            insn: index,
+                            errno: Errno::EBADF,
+                            msg: "invalid map file descriptor",
+                        })?;
+                        if next.imm != 0 {
+                            return Err(error("reserved fields are not zeros"));
+                        }
+                        let map_index = match maps.iter().position(|m| Arc::ptr_eq(m, &map)) {
+                            Some(map_index) => map_index,
+                            None => {
+                                maps.push(map);
+                                maps.len() - 1
+                            }
+                        };
+                        insns[index].imm = map_index as i32;
+                    }
+                    _ => return Err(error("unsupported 64-bit immediate load")),
+                }
+                check_write(insn.dst())?;
+                next_regs |= 1 << insn.dst();
+                width = 2;
+            }
+            BPF_LDX => {
+                if insn.mode() != BPF_MEM || insn.imm != 0 {
+                    return Err(error("unsupported load"));
+                }
+                check_read(insn.src())?;
+                check_write(insn.dst())?;
+                next_regs |= 1 << insn.dst();
+            }
+            BPF_ST => {
+                if insn.mode() != BPF_MEM || insn.src() != 0 {
+                    return Err(error("unsupported store"));
+                }
+                check_read(insn.dst())?;
+            }
+            BPF_STX => {
+                if insn.mode() != BPF_MEM || insn.imm != 0 {
+                    return Err(error("unsupported store"));
+                }
+                check_read(insn.dst())?;
+                check_read(insn.src())?;
+            }
+            _ => unreachable!("the class has three bits"),
+        }
+
+        let mut merge = |target: usize| {
+            states[target] = Some(states[target].map_or(next_regs, |regs| regs & next_regs));
+        };
+        if let Some(target) = jump_target {
+            if target <= index as isize {
+                return Err(error("loops are not supported"));
+            }
+            if target as usize >= nr_insns {
+                return Err(error("jumping out of bounds"));
+            }
+            merge(target as usize);
+        }
+        if falls_through {
+            if index + width >= nr_insns {
+                return Err(error("falling off the end of the program"));
+            }
+            merge(index + width);
+        }
+        if width == 2 && states[index + 1].is_some() {
+            return Err(error("jumping into the middle of an instruction"));
+        }
+
+        index += width;
+    }
+
+    Ok(maps)
+}
@@ -0,0 +1,314 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The interpreter of the eBPF programs.
+//!
+//! The programs never access the kernel memory directly. Instead, each memory region that a
+//! program can access is identified by an index, which is stored in the high 32 bits of the
+//! addresses, and each access is checked against the bounds of the region. The regions are
+//! the stack, the context, and the map values returned by the helper functions. A program
+//! that accesses the memory out of the regions is aborted.
+
+use super::{
+    helpers::BpfHelper,
+    insn::*,
+    map::{BpfMap, BpfMapValue},
+    prog::BpfProg,
+};
+use crate::prelude::*;
+
+/// The size of the stack of a program.
+pub const STACK_SIZE: usize = 512;
+
+/// The tag in the values that refer to the maps, whose low bits are the indexes of the maps.
+const MAP_HANDLE_TAG: u64 = 0xffff_0000_0000_0000;
+
+const REGION_SHIFT: u32 = 32;
+const STACK_REGION: u64 = 1;
+const CTX_REGION: u64 = 2;
+const FIRST_MAP_VALUE_REGION: u64 = 3;
+
+/// The state of a running program.
+pub struct Vm<'a> {
+    prog: &'a BpfProg,
+    regs: [u64; NR_REGS],
+    stack: [u8; STACK_SIZE],
+    ctx: &'a [u8],
+    map_values: Vec<Arc<BpfMapValue>>,
+}
+
+impl<'a> Vm<'a> {
+    pub fn new(prog: &'a BpfProg, ctx: &'a [u8]) -> Self {
+        let mut regs = [0; NR_REGS];
+        regs[1] = CTX_REGION << REGION_SHIFT;
+        regs[FRAME_POINTER] = (STACK_REGION << REGION_SHIFT) + STACK_SIZE as u64;
+
+        Self {
+            prog,
+            regs,
+            stack: [0; STACK_SIZE],
+            ctx,
+            map_values: Vec::new(),
+        }
+    }
+
+    /// Runs the program until it exits, and returns the value in R0.
+    pub fn run(mut self) -> Result<u64> {
+        let prog: &'a BpfProg = self.prog;
+        let insns = prog.insns();
+        let mut pc = 0;
+
+        loop {
+            // The verifier ensures that the program never falls off the end, and that every
+            // jump is forward, so the loop always terminates.
+            let insn = insns[pc];
+            pc += 1;
+
+            match insn.class() {
+                BPF_ALU => self.exec_alu32(&insn),
+                BPF_ALU64 => self.exec_alu64(&insn),
+                BPF_JMP | BPF_JMP32 => match insn.op() {
+                    BPF_CALL => {
+                        let helper = BpfHelper::try_from(insn.imm).unwrap();
+                        self.regs[0] = helper.call(&mut self)?;
+                    }
+                    BPF_EXIT => return Ok(self.regs[0]),
+                    _ => {
+                        if self.is_jump_taken(&insn) {
+                            pc = (pc as isize + insn.off as isize) as usize;
+                        }
+                    }
+                },
+                BPF_LD => {
+                    let high = insns[pc].imm as u32 as u64;
+                    pc += 1;
+                    let imm = (high << 32) | insn.imm as u32 as u64;
+                    self.regs[insn.dst()] = if insn.src() == BPF_PSEUDO_MAP_FD {
+                        MAP_HANDLE_TAG | imm
+                    } else {
+                        imm
+                    };
+                }
+                BPF_LDX => {
+                    let addr = self.regs[insn.src()].wrapping_add(insn.off as i64 as u64);
+                    self.regs[insn.dst()] = self.load(addr, insn.size())?;
+                }
+                class @ (BPF_ST | BPF_STX) => {
+                    let addr = self.regs[insn.dst()].wrapping_add(insn.off as i64 as u64);
+                    let value = if class == BPF_ST {
+                        insn.imm as i64 as u64
+                    } else {
+                        self.regs[insn.src()]
+                    };
+                    self.store(addr, insn.size(), value)?;
+                }
+                _ => unreachable!("the class has three bits"),
+            }
+        }
+    }
+
+    fn exec_alu64(&mut self, insn: &BpfInsn) {
+        let dst = self.regs[insn.dst()];
+        let src = if insn.is_src_reg() {
+            self.regs[insn.src()]
+        } else {
+            insn.imm as i64 as u64
+        };
+
+        self.regs[insn.dst()] = match insn.op() {
+            BPF_ADD => dst.wrapping_add(src),
+            BPF_SUB => dst.wrapping_sub(src),
+            BPF_MUL => dst.wrapping_mul(src),
+            BPF_DIV => dst.checked_div(src).unwrap_or(0),
+            BPF_OR => dst | src,
+            BPF_AND => dst & src,
+            BPF_LSH => dst << (src & 63),
+            BPF_RSH => dst >> (src & 63),
+            BPF_NEG => dst.wrapping_neg(),
+            BPF_MOD => dst.checked_rem(src).unwrap_or(dst),
+            BPF_XOR => dst ^ src,
+            BPF_MOV => src,
+            BPF_ARSH => ((dst as i64) >> (src & 63)) as u64,
+            _ => unreachable!("the operation has been verified"),
+        };
+    }
+
+    fn exec_alu32(&mut self, insn: &BpfInsn) {
+        let dst = self.regs[insn.dst()] as u32;
+        let src = if insn.is_src_reg() {
+            self.regs[insn.src()] as u32
+        } else {
+            insn.imm as u32
+        };
+
+        // The results of the 32-bit operations are zero-extended.
+        self.regs[insn.dst()] = match insn.op() {
+            BPF_ADD => dst.wrapping_add(src) as u64,
+            BPF_SUB => dst.wrapping_sub(src) as u64,
+            BPF_MUL => dst.wrapping_mul(src) as u64,
+            BPF_DIV => dst.checked_div(src).unwrap_or(0) as u64,
+            BPF_OR => (dst | src) as u64,
+            BPF_AND => (dst & src) as u64,
+            BPF_LSH => (dst << (src & 31)) as u64,
+            BPF_RSH => (dst >> (src & 31)) as u64,
+            BPF_NEG => dst.wrapping_neg() as u64,
+            BPF_MOD => dst.checked_rem(src).unwrap_or(dst) as u64,
+            BPF_XOR => (dst ^ src) as u64,
+            BPF_MOV => src as u64,
+            BPF_ARSH => ((dst as i32) >> (src & 31)) as u32 as u64,
+            BPF_END => {
+                let value = self.regs[insn.dst()];
+                // The source bit selects the big endian.
+                match (insn.is_src_reg(), insn.imm) {
+                    (false, 16) => (value as u16).to_le() as u64,
+                    (false, 32) => (value as u32).to_le() as u64,
+                    (false, _) => value.to_le(),
+                    (true, 16) => (value as u16).to_be() as u64,
+                    (true, 32) => (value as u32).to_be() as u64,
+                    (true, _) => value.to_be(),
+                }
+            }
+            _ => unreachable!("the operation has been verified"),
+        };
+    }
+
+    fn is_jump_taken(&self, insn: &BpfInsn) -> bool {
+        let (dst, src) = if insn.class() == BPF_JMP32 {
+            let src = if insn.is_src_reg() {
+                self.regs[insn.src()] as u32
+            } else {
+                insn.imm as u32
+            };
+            // The 32-bit values are sign-extended for the signed comparisons.
+            match insn.op() {
+                BPF_JSGT | BPF_JSGE | BPF_JSLT | BPF_JSLE => (
+                    self.regs[insn.dst()] as i32 as i64 as u64,
+                    src as i32 as i64 as u64,
+                ),
+                _ => (self.regs[insn.dst()] as u32 as u64, src as u64),
+            }
+        } else {
+            let src = if insn.is_src_reg() {
+                self.regs[insn.src()]
+            } else {
+                insn.imm as i64 as u64
+            };
+            (self.regs[insn.dst()], src)
+        };
+
+        match insn.op() {
+            BPF_JA => true,
+            BPF_JEQ => dst == src,
+            BPF_JGT => dst > src,
+            BPF_JGE => dst >= src,
+            BPF_JSET => dst & src != 0,
+            BPF_JNE => dst != src,
+            BPF_JSGT => (dst as i64) > (src as i64),
+            BPF_JSGE => (dst as i64) >= (src as i64),
+            BPF_JLT => dst < src,
+            BPF_JLE => dst <= src,
+            BPF_JSLT => (dst as i64) < (src as i64),
+            BPF_JSLE => (dst as i64) <= (src as i64),
+            _ => unreachable!("the operation has been verified"),
+        }
+    }
+
+    fn load(&self, addr: u64, size: u8) -> Result<u64> {
+        let value = match size {
+            BPF_B => {
+                let mut buf = [0u8; 1];
+                self.read_mem(addr, &mut buf)?;
+                u8::from_ne_bytes(buf) as u64
+            }
+            BPF_H => {
+                let mut buf = [0u8; 2];
+                self.read_mem(addr, &mut buf)?;
+                u16::from_ne_bytes(buf) as u64
+            }
+            BPF_W => {
+                let mut buf = [0u8; 4];
+                self.read_mem(addr, &mut buf)?;
+                u32::from_ne_bytes(buf) as u64
+            }
+            _ => {
+                let mut buf = [0u8; 8];
+                self.read_mem(addr, &mut buf)?;
+                u64::from_ne_bytes(buf)
+            }
+        };
+        Ok(value)
+    }
+
+    fn store(&mut self, addr: u64, size: u8, value: u64) -> Result<()> {
+        match size {
+            BPF_B => self.write_mem(addr, &(value as u8).to_ne_bytes()),
+            BPF_H => self.write_mem(addr, &(value as u16).to_ne_bytes()),
+            BPF_W => self.write_mem(addr, &(value as u32).to_ne_bytes()),
+            _ => self.write_mem(addr, &value.to_ne_bytes()),
+        }
+    }
+
+    /// Returns the argument of a helper function, i.e., R1 to R5.
+    pub fn arg(&self, index: usize) -> u64 {
+        debug_assert!((1..=5).contains(&index));
+        self.regs[index]
+    }
+
+    /// Returns the map referred to by the value.
+    pub fn map(&self, handle: u64) -> Result<&'a Arc<BpfMap>> {
+        if handle & MAP_HANDLE_TAG != MAP_HANDLE_TAG {
+            return_errno_with_message!(Errno::EFAULT, "the value does not refer to a map");
+        }
+        let prog: &'a BpfProg = self.prog;
+        prog.maps()
+            .get((handle & !MAP_HANDLE_TAG) as usize)
+            .ok_or_else(|| Error::with_message(Errno::EFAULT, "the map does not exist"))
+    }
+
+    /// Makes the map value accessible by the program, and returns its address.
+    pub fn expose_map_value(&mut self, value: Arc<BpfMapValue>) -> u64 {
+        let region = FIRST_MAP_VALUE_REGION + self.map_values.len() as u64;
+        self.map_values.push(value);
+        region << REGION_SHIFT
+    }
+
+    /// Reads the memory of the program at the address.
+    pub fn read_mem(&self, addr: u64, buf: &mut [u8]) -> Result<()> {
+        let offset = (addr & ((1 << REGION_SHIFT) - 1)) as usize;
+        let is_ok = match addr >> REGION_SHIFT {
+            STACK_REGION => read_slice(&self.stack, offset, buf),
+            CTX_REGION => read_slice(self.ctx, offset, buf),
+            region => self
+                .map_value(region)
+                .and_then(|value| value.read(offset, buf)),
+        };
+        is_ok.ok_or_else(|| Error::with_message(Errno::EFAULT, "invalid memory read"))
+    }
+
+    /// Writes the memory of the program at the address.
+    ///
+    /// The context is read-only.
+    pub fn write_mem(&mut self, addr: u64, buf: &[u8]) -> Result<()> {
+        let offset = (addr & ((1 << REGION_SHIFT) - 1)) as usize;
+        let is_ok = match addr >> REGION_SHIFT {
+            STACK_REGION => self
+                .stack
+                .get_mut(offset..offset + buf.len())
+                .map(|dst| dst.copy_from_slice(buf)),
+            CTX_REGION => None,
+            region => self
+                .map_value(region)
+                .and_then(|value| value.write(offset, buf)),
+        };
+        is_ok.ok_or_else(|| Error::with_message(Errno::EFAULT, "invalid memory write"))
+    }
+
+    fn map_value(&self, region: u64) -> Option<&Arc<BpfMapValue>> {
+        let index = region.checked_sub(FIRST_MAP_VALUE_REGION)?;
+        self.map_values.get(index as usize)
+    }
+}
+
+fn read_slice(src: &[u8], offset: usize, buf: &mut [u8]) -> Option<()> {
+    buf.copy_from_slice(src.get(offset..offset.checked_add(buf.len())?)?);
+    Some(())
+}
@@ -1,5 +1,11 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use core::fmt::Write;
+
+use ostd::cpu::CpuId;
+
+use crate::prelude::*;
+
 pub trait LinuxAbi {
     /// Get number of syscall
     fn syscall_num(&self) -> usize;
@@ -21,4 +27,40 @@ pub trait LinuxAbi {
 
     /// Get thread-local storage pointer
     fn tls_pointer(&self) -> usize;
+
+    /// Returns whether a 32-bit program is running, whose system calls are handled by the
+    /// compat system call table
+    fn is_compat(&self) -> bool;
+
+    /// Sets whether a 32-bit program is running
+    fn set_compat(&mut self, is_compat: bool);
+}
+
+/// The information of a CPU that is shown in `/proc/cpuinfo`, which is collected by each
+/// architecture.
+pub trait ArchCpuInfo: Sized {
+    /// Collects the information of the CPU.
+    fn new(cpu: CpuId) -> Self;
+
+    /// Returns the fields as pairs of the names and the values, in the order of Linux.
+    fn fields(&self) -> Vec<(&'static str, String)>;
+
+    /// Renders the information as an entry of `/proc/cpuinfo`.
+    fn render_procfs(&self) -> String {
+        let mut output = String::new();
+        for (name, value) in self.fields() {
+            // The names are padded with tabs to 16 columns, as Linux does.
+            let tabs = match name.len() {
+                0..8 => "\t\t",
+                8..16 => "\t",
+                _ => "",
+            };
+            if value.is_empty() {
+                writeln!(output, "{}{}:", name, tabs).unwrap();
+            } else {
+                writeln!(output, "{}{}: {}", name, tabs, value).unwrap();
+            }
+        }
+        output
+    }
 }
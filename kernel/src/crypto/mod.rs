@@ -0,0 +1,22 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The crypto API of the kernel.
+//!
+//! The registry and the software implementations of the algorithms live in `aster-crypto`
+//! (see [`aster_crypto::api`]), so that the components (e.g., `aster-dm`) can also use them.
+//! This module registers the drivers that are accelerated by the hardware, which are preferred
+//! to the software implementations by the lookups.
+
+#[cfg(target_arch = "riscv64")]
+mod zkn;
+
+/// The priority of the hardware-accelerated drivers.
+///
+/// This is the same as the priority of most architecture-specific drivers in Linux.
+#[cfg_attr(not(target_arch = "riscv64"), expect(dead_code))]
+const ACCELERATED_PRIORITY: u32 = 300;
+
+pub(crate) fn init() {
+    #[cfg(target_arch = "riscv64")]
+    zkn::register_drivers();
+}
@@ -0,0 +1,278 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The drivers accelerated by the scalar cryptography extensions of RISC-V.
+//!
+//! The AES driver requires both Zkne and Zknd, and the SHA-2 drivers require Zknh.
+
+use aster_crypto::{
+    aes::{self, Aes},
+    api::{self, Algorithm, BlockCipher, BlockCipherKey, Driver, Sha256Hash, Sha512Hash},
+    sha2::{
+        self, Sha256Sigma, Sha512Sigma, SHA224_DIGEST_LEN, SHA256_BLOCK_LEN, SHA256_DIGEST_LEN,
+        SHA384_DIGEST_LEN, SHA512_BLOCK_LEN, SHA512_DIGEST_LEN,
+    },
+};
+use ostd::arch::crypto::{self, Zknd, Zkne, Zknh};
+
+use super::ACCELERATED_PRIORITY;
+use crate::prelude::*;
+
+pub(super) fn register_drivers() {
+    let driver = |name, driver_name, algorithm| Driver {
+        name,
+        driver_name,
+        priority: ACCELERATED_PRIORITY,
+        algorithm,
+    };
+
+    let mut drivers = Vec::new();
+    if let (Some(zkne), Some(zknd)) = (crypto::zkne(), crypto::zknd()) {
+        drivers.push(driver(
+            "aes",
+            "aes-riscv64-zkn",
+            Algorithm::BlockCipher(Arc::new(ZknAes { zkne, zknd })),
+        ));
+    }
+    if crypto::zknh().is_some() {
+        drivers.extend([
+            driver(
+                "sha224",
+                "sha224-riscv64-zknh",
+                Algorithm::Hash(Arc::new(Sha256Hash::new(
+                    SHA224_DIGEST_LEN,
+                    compress256_zknh,
+                ))),
+            ),
+            driver(
+                "sha256",
+                "sha256-riscv64-zknh",
+                Algorithm::Hash(Arc::new(Sha256Hash::new(
+                    SHA256_DIGEST_LEN,
+                    compress256_zknh,
+                ))),
+            ),
+            driver(
+                "sha384",
+                "sha384-riscv64-zknh",
+                Algorithm::Hash(Arc::new(Sha512Hash::new(
+                    SHA384_DIGEST_LEN,
+                    compress512_zknh,
+                ))),
+            ),
+            driver(
+                "sha512",
+                "sha512-riscv64-zknh",
+                Algorithm::Hash(Arc::new(Sha512Hash::new(
+                    SHA512_DIGEST_LEN,
+                    compress512_zknh,
+                ))),
+            ),
+        ]);
+    }
+
+    for driver in drivers {
+        let driver_name = driver.driver_name;
+        // The drivers are registered only once, so this never fails.
+        api::register(driver).unwrap();
+        info!("[crypto] Registered {}", driver_name);
+    }
+}
+
+struct ZknAes {
+    zkne: Zkne,
+    zknd: Zknd,
+}
+
+impl BlockCipher for ZknAes {
+    fn block_len(&self) -> usize {
+        aes::BLOCK_LEN
+    }
+
+    fn key_lens(&self) -> &'static [usize] {
+        &[16, 24, 32]
+    }
+
+    fn new_key(&self, key: &[u8]) -> Option<Box<dyn BlockCipherKey>> {
+        // The key schedule is computed by the software, and only the rounds are accelerated.
+        let aes = Aes::new(key)?;
+        let rounds = aes.rounds();
+
+        let mut enc_keys = [[0u64; 2]; aes::MAX_ROUNDS + 1];
+        for (enc_key, round_key) in enc_keys.iter_mut().zip(aes.round_keys()) {
+            *enc_key = to_halves(round_key);
+        }
+
+        // The decryption uses the equivalent inverse cipher, where `InvMixColumns` is applied
+        // to the round keys of the middle rounds.
+        let mut dec_keys = enc_keys;
+        for dec_key in dec_keys[1..rounds].iter_mut() {
+            *dec_key = dec_key.map(|half| self.zknd.aes64im(half));
+        }
+
+        Some(Box::new(ZknAesKey {
+            enc_keys,
+            dec_keys,
+            rounds,
+            zkne: self.zkne,
+            zknd: self.zknd,
+        }))
+    }
+}
+
+struct ZknAesKey {
+    enc_keys: [[u64; 2]; aes::MAX_ROUNDS + 1],
+    dec_keys: [[u64; 2]; aes::MAX_ROUNDS + 1],
+    rounds: usize,
+    zkne: Zkne,
+    zknd: Zknd,
+}
+
+impl BlockCipherKey for ZknAesKey {
+    fn encrypt_block(&self, block: &mut [u8]) {
+        let zkne = self.zkne;
+
+        let [mut s0, mut s1] = xor(to_halves(block), self.enc_keys[0]);
+        for round_key in &self.enc_keys[1..self.rounds] {
+            [s0, s1] = xor([zkne.aes64esm(s0, s1), zkne.aes64esm(s1, s0)], *round_key);
+        }
+        let state = xor(
+            [zkne.aes64es(s0, s1), zkne.aes64es(s1, s0)],
+            self.enc_keys[self.rounds],
+        );
+
+        from_halves(state, block);
+    }
+
+    fn decrypt_block(&self, block: &mut [u8]) {
+        let zknd = self.zknd;
+
+        let [mut s0, mut s1] = xor(to_halves(block), self.dec_keys[self.rounds]);
+        for round_key in self.dec_keys[1..self.rounds].iter().rev() {
+            [s0, s1] = xor([zknd.aes64dsm(s0, s1), zknd.aes64dsm(s1, s0)], *round_key);
+        }
+        let state = xor(
+            [zknd.aes64ds(s0, s1), zknd.aes64ds(s1, s0)],
+            self.dec_keys[0],
+        );
+
+        from_halves(state, block);
+    }
+}
+
+/// Converts a block to the two halves of the state, in which the first byte is the least
+/// significant byte of the low half.
+fn to_halves(block: &[u8]) -> [u64; 2] {
+    [
+        u64::from_le_bytes(block[..8].try_into().unwrap()),
+        u64::from_le_bytes(block[8..16].try_into().unwrap()),
+    ]
+}
+
+fn from_halves(state: [u64; 2], block: &mut [u8]) {
+    block[..8].copy_from_slice(&state[0].to_le_bytes());
+    block[8..16].copy_from_slice(&state[1].to_le_bytes());
+}
+
+fn xor(a: [u64; 2], b: [u64; 2]) -> [u64; 2] {
+    [a[0] ^ b[0], a[1] ^ b[1]]
+}
+
+struct ZknhSigma(Zknh);
+
+impl Sha256Sigma for ZknhSigma {
+    fn sum0(&self, x: u32) -> u32 {
+        self.0.sha256sum0(x)
+    }
+
+    fn sum1(&self, x: u32) -> u32 {
+        self.0.sha256sum1(x)
+    }
+
+    fn sig0(&self, x: u32) -> u32 {
+        self.0.sha256sig0(x)
+    }
+
+    fn sig1(&self, x: u32) -> u32 {
+        self.0.sha256sig1(x)
+    }
+}
+
+impl Sha512Sigma for ZknhSigma {
+    fn sum0(&self, x: u64) -> u64 {
+        self.0.sha512sum0(x)
+    }
+
+    fn sum1(&self, x: u64) -> u64 {
+        self.0.sha512sum1(x)
+    }
+
+    fn sig0(&self, x: u64) -> u64 {
+        self.0.sha512sig0(x)
+    }
+
+    fn sig1(&self, x: u64) -> u64 {
+        self.0.sha512sig1(x)
+    }
+}
+
+fn compress256_zknh(state: &mut [u32; 8], block: &[u8; SHA256_BLOCK_LEN]) {
+    // The driver is registered only if Zknh is available.
+    let sigma = ZknhSigma(crypto::zknh().unwrap());
+    sha2::compress256(&sigma, state, block);
+}
+
+fn compress512_zknh(state: &mut [u64; 8], block: &[u8; SHA512_BLOCK_LEN]) {
+    let sigma = ZknhSigma(crypto::zknh().unwrap());
+    sha2::compress512(&sigma, state, block);
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn aes_matches_generic() {
+        let (Some(zkne), Some(zknd)) = (crypto::zkne(), crypto::zknd()) else {
+            return;
+        };
+        let cipher = ZknAes { zkne, zknd };
+
+        for key_len in [16, 24, 32] {
+            let key: Vec<u8> = (0..key_len as u8).collect();
+            let generic = Aes::new(&key).unwrap();
+            let accelerated = cipher.new_key(&key).unwrap();
+
+            let mut expected = [0x5au8; aes::BLOCK_LEN];
+            generic.encrypt_block(&mut expected);
+            let mut block = [0x5au8; aes::BLOCK_LEN];
+            accelerated.encrypt_block(&mut block);
+            assert_eq!(block, expected);
+
+            accelerated.decrypt_block(&mut block);
+            assert_eq!(block, [0x5au8; aes::BLOCK_LEN]);
+        }
+    }
+
+    #[ktest]
+    fn sha2_matches_generic() {
+        if crypto::zknh().is_none() {
+            return;
+        }
+
+        let block = [0xa5u8; SHA512_BLOCK_LEN];
+
+        let mut expected = [1u32; 8];
+        sha2::compress256_generic(&mut expected, block[..SHA256_BLOCK_LEN].try_into().unwrap());
+        let mut state = [1u32; 8];
+        compress256_zknh(&mut state, block[..SHA256_BLOCK_LEN].try_into().unwrap());
+        assert_eq!(state, expected);
+
+        let mut expected = [1u64; 8];
+        sha2::compress512_generic(&mut expected, &block);
+        let mut state = [1u64; 8];
+        compress512_zknh(&mut state, &block);
+        assert_eq!(state, expected);
+    }
+}
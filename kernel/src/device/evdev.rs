@@ -0,0 +1,558 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The evdev devices, i.e., `/dev/input/eventN`.
+//!
+//! Each input device of `aster-input` is exposed as an evdev device. The events reported by
+//! the device are queued for each open file, and become readable as `struct input_event`s
+//! once a `SYN_REPORT` arrives. Writing `struct input_event`s to the file injects them as if
+//! they were reported by the device, which allows the user space to emulate input devices.
+//! The capabilities and the states of the device are queried with the `EVIOCG*` ioctls.
+//!
+//! Reference: <https://docs.kernel.org/input/input.html#evdev>.
+
+use alloc::{collections::VecDeque, format};
+use core::{
+    mem::size_of,
+    sync::atomic::{AtomicU32, AtomicU64, Ordering},
+    time::Duration,
+};
+
+use aster_input::{
+    event::{ABS_CNT, EV_LED, EV_SND, EV_SW, EV_SYN, KEY_CNT, SYN_DROPPED},
+    InputCapability, InputDevice, InputEvent,
+};
+use ostd::sync::LocalIrqDisabled;
+use spin::Once;
+
+use crate::{
+    events::IoEvents,
+    fs::{
+        device::{add_node, Device, DeviceId, DeviceType},
+        inode_handle::FileIo,
+        utils::StatusFlags,
+    },
+    prelude::*,
+    process::signal::{PollHandle, Pollable, Pollee},
+    syscall::ClockId,
+    time::{
+        clockid_t,
+        clocks::{BootTimeClock, MonotonicClock, RealTimeClock},
+        timeval_t,
+    },
+};
+
+/// The major number of the input devices.
+const INPUT_MAJOR: u32 = 13;
+/// The first minor number of `/dev/input/eventN`.
+pub(super) const EVDEV_MINOR_BASE: u32 = 64;
+/// The number of the evdev devices, which is the same as Linux.
+pub(super) const EVDEV_MINORS: u32 = 32;
+
+/// The version of the evdev protocol.
+const EV_VERSION: i32 = 0x010001;
+
+/// The maximum number of the events queued for an open file.
+const EVDEV_BUFFER_SIZE: usize = 256;
+
+// The numbers of the evdev ioctls, i.e., the `_IOC_NR` of `EVIOC*`.
+const EVIOCGVERSION: u32 = 0x01;
+const EVIOCGID: u32 = 0x02;
+const EVIOCGNAME: u32 = 0x06;
+const EVIOCGPHYS: u32 = 0x07;
+const EVIOCGUNIQ: u32 = 0x08;
+const EVIOCGPROP: u32 = 0x09;
+const EVIOCGKEY: u32 = 0x18;
+const EVIOCGLED: u32 = 0x19;
+const EVIOCGSND: u32 = 0x1a;
+const EVIOCGSW: u32 = 0x1b;
+const EVIOCGBIT: u32 = 0x20;
+const EVIOCGABS: u32 = 0x40;
+const EVIOCGRAB: u32 = 0x90;
+const EVIOCSCLOCKID: u32 = 0xa0;
+
+// The directions of the ioctls.
+const IOC_WRITE: u32 = 1;
+const IOC_READ: u32 = 2;
+
+/// The type of the evdev ioctls.
+const EVDEV_IOC_TYPE: u32 = b'E' as u32;
+
+static EVDEVS: Once<Vec<Arc<Evdev>>> = Once::new();
+
+/// The ID of the next open file of the evdev devices.
+static NEXT_CLIENT_ID: AtomicU64 = AtomicU64::new(0);
+
+pub(super) fn init() -> Result<()> {
+    let evdevs = EVDEVS.call_once(|| {
+        aster_input::all_devices()
+            .into_iter()
+            .take(EVDEV_MINORS as usize)
+            .enumerate()
+            .map(|(index, (_, device))| Evdev::new(index as u32, device))
+            .collect()
+    });
+
+    for evdev in evdevs.iter() {
+        let name = format!("input/event{}", evdev.index);
+        add_node(
+            Arc::new(EvdevDevice {
+                evdev: evdev.clone(),
+            }),
+            &name,
+        )?;
+    }
+    Ok(())
+}
+
+/// Returns the evdev device with the minor number.
+pub(super) fn get_device(minor: u32) -> Result<Arc<dyn Device>> {
+    let evdev = EVDEVS
+        .get()
+        .and_then(|evdevs| evdevs.get((minor - EVDEV_MINOR_BASE) as usize));
+    let Some(evdev) = evdev else {
+        return_errno_with_message!(Errno::ENODEV, "the input device does not exist");
+    };
+    Ok(Arc::new(EvdevDevice {
+        evdev: evdev.clone(),
+    }))
+}
+
+/// `struct input_event` in Linux.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct CInputEvent {
+    time: timeval_t,
+    type_: u16,
+    code: u16,
+    value: i32,
+}
+
+/// `struct input_id` in Linux.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct CInputId {
+    bus_type: u16,
+    vendor: u16,
+    product: u16,
+    version: u16,
+}
+
+/// `struct input_absinfo` in Linux.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct CInputAbsInfo {
+    value: i32,
+    minimum: i32,
+    maximum: i32,
+    fuzz: i32,
+    flat: i32,
+    resolution: i32,
+}
+
+/// An input device and its open files.
+struct Evdev {
+    index: u32,
+    device: Arc<dyn InputDevice>,
+    capability: InputCapability,
+    state: SpinLock<EvdevState, LocalIrqDisabled>,
+}
+
+struct EvdevState {
+    clients: Vec<Weak<EvdevClient>>,
+    /// The ID of the client that grabs the device, i.e., receives all the events exclusively.
+    grab: Option<u64>,
+    /// The keys that are pressed.
+    keys: [u64; KEY_CNT / 64],
+    /// The values of the absolute axes.
+    abs: [i32; ABS_CNT],
+}
+
+impl Evdev {
+    fn new(index: u32, device: Arc<dyn InputDevice>) -> Arc<Self> {
+        let evdev = Arc::new(Self {
+            index,
+            capability: device.capability(),
+            device: device.clone(),
+            state: SpinLock::new(EvdevState {
+                clients: Vec::new(),
+                grab: None,
+                keys: [0; KEY_CNT / 64],
+                abs: [0; ABS_CNT],
+            }),
+        });
+
+        // The evdev devices live forever, so the leaked callback is fine.
+        let callback = {
+            let evdev = evdev.clone();
+            move |event| evdev.handle_event(event)
+        };
+        device.register_callbacks(Box::leak(Box::new(callback)));
+
+        evdev
+    }
+
+    /// Updates the states of the device and passes the event to the clients.
+    fn handle_event(&self, event: InputEvent) {
+        let (type_, code, value) = event.to_raw();
+
+        let clients: Vec<_> = {
+            let mut state = self.state.lock();
+            state.update(&event);
+
+            state.clients.retain(|client| client.strong_count() > 0);
+            let grab = state.grab;
+            state
+                .clients
+                .iter()
+                .filter_map(Weak::upgrade)
+                .filter(|client| grab.is_none_or(|id| id == client.id))
+                .collect()
+        };
+
+        // The clients are passed the events without the lock held, since dropping a
+        // client takes the lock.
+        for client in clients.iter() {
+            client.push(type_, code, value);
+        }
+    }
+}
+
+impl EvdevState {
+    fn update(&mut self, event: &InputEvent) {
+        let (_, code, value) = event.to_raw();
+        match event {
+            InputEvent::KeyBoard(..) | InputEvent::Button(..) => {
+                let bit = 1 << (code % 64);
+                if value != 0 {
+                    self.keys[code as usize / 64] |= bit;
+                } else {
+                    self.keys[code as usize / 64] &= !bit;
+                }
+            }
+            InputEvent::Absolute(..) => self.abs[code as usize] = value,
+            InputEvent::Relative(..) | InputEvent::Sync => {}
+        }
+    }
+}
+
+/// Corresponds to `/dev/input/eventN` in the file system.
+struct EvdevDevice {
+    evdev: Arc<Evdev>,
+}
+
+impl Device for EvdevDevice {
+    fn type_(&self) -> DeviceType {
+        DeviceType::CharDevice
+    }
+
+    fn id(&self) -> DeviceId {
+        DeviceId::new(INPUT_MAJOR, EVDEV_MINOR_BASE + self.evdev.index)
+    }
+
+    fn open(&self) -> Result<Option<Arc<dyn FileIo>>> {
+        let client = Arc::new(EvdevClient {
+            id: NEXT_CLIENT_ID.fetch_add(1, Ordering::Relaxed),
+            evdev: self.evdev.clone(),
+            queue: SpinLock::new(EventQueue {
+                events: VecDeque::new(),
+                nr_ready: 0,
+            }),
+            clock_id: AtomicU32::new(ClockId::CLOCK_REALTIME as u32),
+            pollee: Pollee::new(),
+        });
+        self.evdev
+            .state
+            .lock()
+            .clients
+            .push(Arc::downgrade(&client));
+        Ok(Some(client))
+    }
+}
+
+impl Pollable for EvdevDevice {
+    fn poll(&self, mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        let events = IoEvents::empty();
+        events & mask
+    }
+}
+
+impl FileIo for EvdevDevice {
+    fn read(&self, _writer: &mut VmWriter) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the input device is not opened");
+    }
+
+    fn write(&self, _reader: &mut VmReader) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the input device is not opened");
+    }
+}
+
+/// An open file of an evdev device.
+struct EvdevClient {
+    id: u64,
+    evdev: Arc<Evdev>,
+    queue: SpinLock<EventQueue, LocalIrqDisabled>,
+    /// The clock of the timestamps of the events.
+    clock_id: AtomicU32,
+    pollee: Pollee,
+}
+
+struct EventQueue {
+    events: VecDeque<CInputEvent>,
+    /// The number of the events that can be read, i.e., those before the last `SYN_REPORT`.
+    nr_ready: usize,
+}
+
+impl EvdevClient {
+    fn push(&self, type_: u16, code: u16, value: i32) {
+        let event = CInputEvent {
+            time: timeval_t::from(self.now()),
+            type_,
+            code,
+            value,
+        };
+
+        let mut queue = self.queue.lock();
+        if queue.events.len() >= EVDEV_BUFFER_SIZE {
+            // Like Linux, all the queued events are dropped, and the user space is told so by
+            // a `SYN_DROPPED` event.
+            queue.events.clear();
+            queue.events.push_back(CInputEvent {
+                type_: EV_SYN,
+                code: SYN_DROPPED,
+                value: 0,
+                ..event
+            });
+            queue.nr_ready = 1;
+        }
+        queue.events.push_back(event);
+        if InputEvent::from_raw(type_, code, value) == Some(InputEvent::Sync) {
+            queue.nr_ready = queue.events.len();
+        }
+
+        let is_ready = queue.nr_ready > 0;
+        drop(queue);
+        if is_ready {
+            self.pollee.notify(IoEvents::IN);
+        }
+    }
+
+    fn now(&self) -> Duration {
+        match ClockId::try_from(self.clock_id.load(Ordering::Relaxed) as clockid_t) {
+            Ok(ClockId::CLOCK_MONOTONIC) => MonotonicClock::get().read_time(),
+            Ok(ClockId::CLOCK_BOOTTIME) => BootTimeClock::get().read_time(),
+            _ => RealTimeClock::get().read_time(),
+        }
+    }
+
+    fn try_read(&self, writer: &mut VmWriter) -> Result<usize> {
+        let max_events = writer.avail() / size_of::<CInputEvent>();
+        if max_events == 0 {
+            return_errno_with_message!(Errno::EINVAL, "the buffer is too small for an event");
+        }
+
+        // The events are taken out before writing them, since the user memory may not be
+        // accessed with the spin lock held.
+        let events: Vec<_> = {
+            let mut queue = self.queue.lock();
+            if queue.nr_ready == 0 {
+                return_errno_with_message!(Errno::EAGAIN, "no event is available");
+            }
+            let nr_events = queue.nr_ready.min(max_events);
+            queue.nr_ready -= nr_events;
+            if queue.nr_ready == 0 {
+                self.pollee.invalidate();
+            }
+            queue.events.drain(..nr_events).collect()
+        };
+
+        let bytes: Vec<u8> = events
+            .iter()
+            .flat_map(|event| event.as_bytes().iter().copied())
+            .collect();
+        writer.write_fallible(&mut VmReader::from(bytes.as_slice()))?;
+        Ok(bytes.len())
+    }
+
+    fn check_io_events(&self) -> IoEvents {
+        let mut events = IoEvents::OUT;
+        if self.queue.lock().nr_ready > 0 {
+            events |= IoEvents::IN;
+        }
+        events
+    }
+
+    fn set_grab(&self, is_grabbed: bool) -> Result<()> {
+        let mut state = self.evdev.state.lock();
+        if is_grabbed {
+            if state.grab.is_some() {
+                return_errno_with_message!(Errno::EBUSY, "the device is already grabbed");
+            }
+            state.grab = Some(self.id);
+        } else {
+            if state.grab != Some(self.id) {
+                return_errno_with_message!(Errno::EINVAL, "the device is not grabbed");
+            }
+            state.grab = None;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for EvdevClient {
+    fn drop(&mut self) {
+        let mut state = self.evdev.state.lock();
+        if state.grab == Some(self.id) {
+            state.grab = None;
+        }
+    }
+}
+
+impl Pollable for EvdevClient {
+    fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
+        self.pollee
+            .poll_with(mask, poller, || self.check_io_events())
+    }
+}
+
+impl FileIo for EvdevClient {
+    fn read(&self, writer: &mut VmWriter) -> Result<usize> {
+        self.read_with_flags(writer, StatusFlags::empty())
+    }
+
+    fn write(&self, reader: &mut VmReader) -> Result<usize> {
+        let buf = reader.collect()?;
+        if buf.len() < size_of::<CInputEvent>() {
+            return_errno_with_message!(Errno::EINVAL, "the buffer is too small for an event");
+        }
+
+        let events = buf.chunks_exact(size_of::<CInputEvent>());
+        let len = events.len() * size_of::<CInputEvent>();
+        for bytes in events {
+            let event = CInputEvent::from_bytes(bytes);
+            // The events that the device cannot report are ignored, like Linux does.
+            let Some(event) = InputEvent::from_raw(event.type_, event.code, event.value) else {
+                continue;
+            };
+            if self.evdev.capability.supports(&event) {
+                self.evdev.handle_event(event);
+            }
+        }
+        Ok(len)
+    }
+
+    fn read_with_flags(&self, writer: &mut VmWriter, status_flags: StatusFlags) -> Result<usize> {
+        if status_flags.contains(StatusFlags::O_NONBLOCK) {
+            self.try_read(writer)
+        } else {
+            self.wait_events(IoEvents::IN, None, || self.try_read(writer))
+        }
+    }
+
+    fn ioctl_raw(&self, cmd: u32, arg: usize) -> Result<i32> {
+        let dir = cmd >> 30;
+        let size = ((cmd >> 16) & 0x3fff) as usize;
+        let type_ = (cmd >> 8) & 0xff;
+        let nr = cmd & 0xff;
+        if type_ != EVDEV_IOC_TYPE {
+            return_errno_with_message!(Errno::EINVAL, "the ioctl command is unknown");
+        }
+
+        let user_space = current_userspace!();
+        let evdev = &self.evdev;
+
+        match (dir, nr) {
+            (IOC_READ, EVIOCGVERSION) => user_space.write_val(arg, &EV_VERSION)?,
+            (IOC_READ, EVIOCGID) => {
+                let id = evdev.device.id();
+                let id = CInputId {
+                    bus_type: id.bus_type,
+                    vendor: id.vendor,
+                    product: id.product,
+                    version: id.version,
+                };
+                user_space.write_val(arg, &id)?;
+            }
+            (IOC_READ, EVIOCGNAME) => return str_to_user(evdev.device.name(), arg, size),
+            (IOC_READ, EVIOCGPHYS | EVIOCGUNIQ) => {
+                return_errno_with_message!(Errno::ENOENT, "the device has no such string")
+            }
+            (IOC_READ, EVIOCGPROP) => {
+                let props = [evdev.capability.props() as u64];
+                return bits_to_user(&props, arg, size);
+            }
+            (IOC_READ, EVIOCGKEY) => {
+                let keys = evdev.state.lock().keys;
+                return bits_to_user(&keys, arg, size);
+            }
+            (IOC_READ, EVIOCGLED | EVIOCGSND | EVIOCGSW) => {
+                // Such events are never reported, so the states are all zeros.
+                let type_ = match nr {
+                    EVIOCGLED => EV_LED,
+                    EVIOCGSND => EV_SND,
+                    _ => EV_SW,
+                };
+                let bits = evdev.capability.bitmap(type_).unwrap();
+                return bits_to_user(&bits, arg, size);
+            }
+            (IOC_READ, nr) if (EVIOCGBIT..EVIOCGBIT + 0x20).contains(&nr) => {
+                let Some(bits) = evdev.capability.bitmap((nr - EVIOCGBIT) as u16) else {
+                    return_errno_with_message!(Errno::EINVAL, "the event type is unknown");
+                };
+                return bits_to_user(&bits, arg, size);
+            }
+            (IOC_READ, nr) if (EVIOCGABS..EVIOCGABS + ABS_CNT as u32).contains(&nr) => {
+                let axis = (nr - EVIOCGABS) as u16;
+                let Some(info) = evdev.capability.abs_info(axis) else {
+                    return_errno_with_message!(Errno::EINVAL, "the absolute axis is unsupported");
+                };
+                let abs_info = CInputAbsInfo {
+                    value: evdev.state.lock().abs[axis as usize],
+                    minimum: info.minimum,
+                    maximum: info.maximum,
+                    fuzz: info.fuzz,
+                    flat: info.flat,
+                    resolution: info.resolution,
+                };
+                let len = size.min(size_of::<CInputAbsInfo>());
+                user_space.write_bytes(arg, &mut VmReader::from(&abs_info.as_bytes()[..len]))?;
+            }
+            (IOC_WRITE, EVIOCGRAB) => self.set_grab(arg != 0)?,
+            (IOC_WRITE, EVIOCSCLOCKID) => {
+                let clock_id: clockid_t = user_space.read_val(arg)?;
+                match ClockId::try_from(clock_id) {
+                    Ok(
+                        ClockId::CLOCK_REALTIME
+                        | ClockId::CLOCK_MONOTONIC
+                        | ClockId::CLOCK_BOOTTIME,
+                    ) => self.clock_id.store(clock_id as u32, Ordering::Relaxed),
+                    _ => return_errno_with_message!(Errno::EINVAL, "the clock is unsupported"),
+                }
+            }
+            _ => return_errno_with_message!(Errno::EINVAL, "the evdev ioctl is unsupported"),
+        }
+
+        Ok(0)
+    }
+}
+
+/// Copies the string with the terminating null byte to the user space, truncating it to
+/// `max_len` bytes.
+fn str_to_user(str: &str, addr: Vaddr, max_len: usize) -> Result<i32> {
+    if str.is_empty() {
+        return_errno_with_message!(Errno::ENOENT, "the device has no such string");
+    }
+
+    let mut bytes = str.as_bytes().to_vec();
+    bytes.push(0);
+    bytes.truncate(max_len);
+    current_userspace!().write_bytes(addr, &mut VmReader::from(bytes.as_slice()))?;
+    Ok(bytes.len() as i32)
+}
+
+/// Copies the bitmap to the user space, truncating it to `max_len` bytes.
+fn bits_to_user(bits: &[u64], addr: Vaddr, max_len: usize) -> Result<i32> {
+    let bytes: Vec<u8> = bits.iter().flat_map(|bits| bits.to_ne_bytes()).collect();
+    let len = bytes.len().min(max_len);
+    current_userspace!().write_bytes(addr, &mut VmReader::from(&bytes[..len]))?;
+    Ok(len as i32)
+}
@@ -0,0 +1,391 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The I2C devices, i.e., `/dev/i2c-N`.
+//!
+//! The adapters are provided by `aster-i2c`. Like the `i2c-dev` of Linux, the address of the
+//! device to talk to is set by the `I2C_SLAVE` ioctl, after which reading and writing the
+//! file transfer the bytes to the device. The combined transfers and the SMBus protocols are
+//! done by the `I2C_RDWR` and the `I2C_SMBUS` ioctls.
+
+use alloc::format;
+use core::sync::atomic::{AtomicU16, Ordering};
+
+use aster_i2c::{I2cAdapter, I2cError, I2cMsg, I2C_SMBUS_BLOCK_MAX};
+
+use crate::{
+    events::IoEvents,
+    fs::{
+        device::{add_node, Device, DeviceId, DeviceType},
+        inode_handle::FileIo,
+        utils::IoctlCmd,
+    },
+    prelude::*,
+    process::signal::{PollHandle, Pollable},
+};
+
+/// The major number of `/dev/i2c-N`.
+pub(super) const I2C_MAJOR: u32 = 89;
+
+/// The maximum number of the bytes of a read or a write, which is the same as Linux.
+const MAX_TRANSFER_LEN: usize = 8192;
+/// The maximum number of the messages of `I2C_RDWR`.
+const I2C_RDWR_IOCTL_MAX_MSGS: u32 = 42;
+
+// The flags of `struct i2c_msg`.
+const I2C_M_RD: u16 = 0x0001;
+const I2C_M_TEN: u16 = 0x0010;
+
+// The functionality of the adapters.
+const I2C_FUNC_I2C: u64 = 0x0000_0001;
+const I2C_FUNC_SMBUS_QUICK: u64 = 0x0001_0000;
+const I2C_FUNC_SMBUS_BYTE: u64 = 0x0006_0000;
+const I2C_FUNC_SMBUS_BYTE_DATA: u64 = 0x0018_0000;
+const I2C_FUNC_SMBUS_WORD_DATA: u64 = 0x0060_0000;
+const I2C_FUNC_SMBUS_PROC_CALL: u64 = 0x0080_0000;
+const I2C_FUNC_SMBUS_WRITE_BLOCK_DATA: u64 = 0x0200_0000;
+const I2C_FUNC_SMBUS_I2C_BLOCK: u64 = 0x0c00_0000;
+
+// The directions and the sizes of `I2C_SMBUS`.
+const I2C_SMBUS_READ: u8 = 1;
+const I2C_SMBUS_QUICK: u32 = 0;
+const I2C_SMBUS_BYTE: u32 = 1;
+const I2C_SMBUS_BYTE_DATA: u32 = 2;
+const I2C_SMBUS_WORD_DATA: u32 = 3;
+const I2C_SMBUS_PROC_CALL: u32 = 4;
+const I2C_SMBUS_BLOCK_DATA: u32 = 5;
+const I2C_SMBUS_I2C_BLOCK_BROKEN: u32 = 6;
+const I2C_SMBUS_I2C_BLOCK_DATA: u32 = 8;
+
+pub(super) fn init() -> Result<()> {
+    for adapter in aster_i2c::all_adapters() {
+        let name = format!("i2c-{}", adapter.index());
+        add_node(Arc::new(I2cDevice { adapter }), &name)?;
+    }
+    Ok(())
+}
+
+/// Returns the I2C device with the minor number.
+pub(super) fn get_device(minor: u32) -> Result<Arc<dyn Device>> {
+    let Some(adapter) = aster_i2c::get_adapter(minor as usize) else {
+        return_errno_with_message!(Errno::ENXIO, "the I2C adapter does not exist");
+    };
+    Ok(Arc::new(I2cDevice { adapter }))
+}
+
+/// `struct i2c_msg` in Linux.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct CI2cMsg {
+    addr: u16,
+    flags: u16,
+    len: u16,
+    _pad: u16,
+    buf: u64,
+}
+
+/// `struct i2c_rdwr_ioctl_data` in Linux.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct CI2cRdwrIoctlData {
+    msgs: u64,
+    nmsgs: u32,
+    _pad: u32,
+}
+
+/// `struct i2c_smbus_ioctl_data` in Linux.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct CI2cSmbusIoctlData {
+    read_write: u8,
+    command: u8,
+    _pad: u16,
+    size: u32,
+    data: u64,
+}
+
+impl From<I2cError> for Error {
+    fn from(err: I2cError) -> Self {
+        match err {
+            I2cError::InvalidArgs => Error::with_message(Errno::EINVAL, "invalid I2C messages"),
+            I2cError::Nack => {
+                Error::with_message(Errno::EREMOTEIO, "the I2C device does not acknowledge")
+            }
+            I2cError::ArbitrationLost => {
+                Error::with_message(Errno::EAGAIN, "the I2C arbitration is lost")
+            }
+            I2cError::Timeout => Error::with_message(Errno::ETIMEDOUT, "the I2C bus times out"),
+            I2cError::NotSupported => {
+                Error::with_message(Errno::EOPNOTSUPP, "the I2C adapter does not support it")
+            }
+            I2cError::IoError => {
+                Error::with_message(Errno::EIO, "the I2C adapter reports an error")
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+struct I2cDevice {
+    adapter: Arc<I2cAdapter>,
+}
+
+impl Debug for I2cDevice {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("I2cDevice")
+            .field("adapter", &self.adapter.name())
+            .finish()
+    }
+}
+
+impl Device for I2cDevice {
+    fn type_(&self) -> DeviceType {
+        DeviceType::CharDevice
+    }
+
+    fn id(&self) -> DeviceId {
+        DeviceId::new(I2C_MAJOR, self.adapter.index() as u32)
+    }
+
+    fn open(&self) -> Result<Option<Arc<dyn FileIo>>> {
+        Ok(Some(Arc::new(I2cFile {
+            adapter: self.adapter.clone(),
+            addr: AtomicU16::new(0),
+        })))
+    }
+}
+
+impl Pollable for I2cDevice {
+    fn poll(&self, mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        let events = IoEvents::IN | IoEvents::OUT;
+        events & mask
+    }
+}
+
+impl FileIo for I2cDevice {
+    fn read(&self, _writer: &mut VmWriter) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the I2C device is not opened");
+    }
+
+    fn write(&self, _reader: &mut VmReader) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the I2C device is not opened");
+    }
+}
+
+/// An opened I2C device, which talks to the device at the address.
+struct I2cFile {
+    adapter: Arc<I2cAdapter>,
+    addr: AtomicU16,
+}
+
+impl I2cFile {
+    fn addr(&self) -> u16 {
+        self.addr.load(Ordering::Relaxed)
+    }
+
+    fn functionality(&self) -> u64 {
+        let mut funcs = I2C_FUNC_I2C
+            | I2C_FUNC_SMBUS_BYTE
+            | I2C_FUNC_SMBUS_BYTE_DATA
+            | I2C_FUNC_SMBUS_WORD_DATA
+            | I2C_FUNC_SMBUS_PROC_CALL
+            | I2C_FUNC_SMBUS_WRITE_BLOCK_DATA
+            | I2C_FUNC_SMBUS_I2C_BLOCK;
+        if self.adapter.supports_quick() {
+            funcs |= I2C_FUNC_SMBUS_QUICK;
+        }
+        funcs
+    }
+
+    fn rdwr(&self, arg: usize) -> Result<i32> {
+        let user_space = current_userspace!();
+        let data: CI2cRdwrIoctlData = user_space.read_val(arg)?;
+        if data.nmsgs == 0 || data.nmsgs > I2C_RDWR_IOCTL_MAX_MSGS {
+            return_errno_with_message!(Errno::EINVAL, "invalid number of I2C messages");
+        }
+
+        let mut c_msgs = Vec::with_capacity(data.nmsgs as usize);
+        let mut bufs = Vec::with_capacity(data.nmsgs as usize);
+        for index in 0..data.nmsgs as usize {
+            let c_msg: CI2cMsg =
+                user_space.read_val(data.msgs as usize + index * size_of::<CI2cMsg>())?;
+            if c_msg.flags & I2C_M_TEN != 0 {
+                return_errno_with_message!(Errno::EINVAL, "10-bit addresses are not supported");
+            }
+            if c_msg.len as usize > MAX_TRANSFER_LEN {
+                return_errno_with_message!(Errno::EINVAL, "the I2C message is too long");
+            }
+
+            let mut buf = vec![0u8; c_msg.len as usize];
+            if c_msg.flags & I2C_M_RD == 0 {
+                user_space
+                    .read_bytes(c_msg.buf as usize, &mut VmWriter::from(buf.as_mut_slice()))?;
+            }
+            c_msgs.push(c_msg);
+            bufs.push(buf);
+        }
+
+        let mut msgs: Vec<I2cMsg> = c_msgs
+            .iter()
+            .zip(bufs.iter_mut())
+            .map(|(c_msg, buf)| {
+                if c_msg.flags & I2C_M_RD != 0 {
+                    I2cMsg::Read {
+                        addr: c_msg.addr,
+                        buf: buf.as_mut_slice(),
+                    }
+                } else {
+                    I2cMsg::Write {
+                        addr: c_msg.addr,
+                        buf: buf.as_slice(),
+                    }
+                }
+            })
+            .collect();
+        self.adapter.transfer(&mut msgs)?;
+        drop(msgs);
+
+        for (c_msg, buf) in c_msgs.iter().zip(bufs.iter()) {
+            if c_msg.flags & I2C_M_RD != 0 {
+                user_space.write_bytes(c_msg.buf as usize, &mut VmReader::from(buf.as_slice()))?;
+            }
+        }
+        Ok(data.nmsgs as i32)
+    }
+
+    fn smbus(&self, arg: usize) -> Result<i32> {
+        let user_space = current_userspace!();
+        let data: CI2cSmbusIoctlData = user_space.read_val(arg)?;
+        let (adapter, addr) = (&self.adapter, self.addr());
+        let is_read = data.read_write == I2C_SMBUS_READ;
+        let data_ptr = data.data as usize;
+
+        match (data.size, is_read) {
+            (I2C_SMBUS_QUICK, _) => adapter.smbus_quick(addr, is_read)?,
+            (I2C_SMBUS_BYTE, true) => {
+                let value = adapter.smbus_read_byte(addr)?;
+                user_space.write_val(data_ptr, &value)?;
+            }
+            // The byte to write is the command.
+            (I2C_SMBUS_BYTE, false) => adapter.smbus_write_byte(addr, data.command)?,
+            (I2C_SMBUS_BYTE_DATA, true) => {
+                let value = adapter.smbus_read_byte_data(addr, data.command)?;
+                user_space.write_val(data_ptr, &value)?;
+            }
+            (I2C_SMBUS_BYTE_DATA, false) => {
+                let value: u8 = user_space.read_val(data_ptr)?;
+                adapter.smbus_write_byte_data(addr, data.command, value)?;
+            }
+            (I2C_SMBUS_WORD_DATA, true) => {
+                let value = adapter.smbus_read_word_data(addr, data.command)?;
+                user_space.write_val(data_ptr, &value)?;
+            }
+            (I2C_SMBUS_WORD_DATA, false) => {
+                let value: u16 = user_space.read_val(data_ptr)?;
+                adapter.smbus_write_word_data(addr, data.command, value)?;
+            }
+            (I2C_SMBUS_PROC_CALL, _) => {
+                let value: u16 = user_space.read_val(data_ptr)?;
+                let value = adapter.smbus_process_call(addr, data.command, value)?;
+                user_space.write_val(data_ptr, &value)?;
+            }
+            (I2C_SMBUS_BLOCK_DATA, false) => {
+                let block = read_block(data_ptr)?;
+                adapter.smbus_write_block_data(addr, data.command, &block)?;
+            }
+            (I2C_SMBUS_I2C_BLOCK_BROKEN | I2C_SMBUS_I2C_BLOCK_DATA, true) => {
+                // The length of the block to read is given by the first byte.
+                let len: u8 = user_space.read_val(data_ptr)?;
+                let len = (len as usize).clamp(1, I2C_SMBUS_BLOCK_MAX);
+                let mut block = [0u8; I2C_SMBUS_BLOCK_MAX + 1];
+                block[0] = len as u8;
+                adapter.smbus_read_i2c_block_data(addr, data.command, &mut block[1..=len])?;
+                user_space.write_bytes(data_ptr, &mut VmReader::from(&block[..=len]))?;
+            }
+            (I2C_SMBUS_I2C_BLOCK_BROKEN | I2C_SMBUS_I2C_BLOCK_DATA, false) => {
+                let block = read_block(data_ptr)?;
+                adapter.smbus_write_i2c_block_data(addr, data.command, &block)?;
+            }
+            _ => return_errno_with_message!(Errno::EOPNOTSUPP, "unsupported SMBus transfer"),
+        }
+        Ok(0)
+    }
+}
+
+/// Reads the block of `union i2c_smbus_data`, whose first byte is the length.
+fn read_block(data_ptr: usize) -> Result<Vec<u8>> {
+    let user_space = current_userspace!();
+    let len: u8 = user_space.read_val(data_ptr)?;
+    if len == 0 || len as usize > I2C_SMBUS_BLOCK_MAX {
+        return_errno_with_message!(Errno::EINVAL, "invalid SMBus block length");
+    }
+    let mut block = vec![0u8; len as usize];
+    user_space.read_bytes(data_ptr + 1, &mut VmWriter::from(block.as_mut_slice()))?;
+    Ok(block)
+}
+
+impl Debug for I2cFile {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("I2cFile")
+            .field("adapter", &self.adapter.name())
+            .field("addr", &self.addr())
+            .finish()
+    }
+}
+
+impl Pollable for I2cFile {
+    fn poll(&self, mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        let events = IoEvents::IN | IoEvents::OUT;
+        events & mask
+    }
+}
+
+impl FileIo for I2cFile {
+    fn read(&self, writer: &mut VmWriter) -> Result<usize> {
+        let mut buf = vec![0u8; writer.avail().min(MAX_TRANSFER_LEN)];
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        self.adapter.transfer(&mut [I2cMsg::Read {
+            addr: self.addr(),
+            buf: &mut buf,
+        }])?;
+        writer.write_fallible(&mut VmReader::from(buf.as_slice()))?;
+        Ok(buf.len())
+    }
+
+    fn write(&self, reader: &mut VmReader) -> Result<usize> {
+        if reader.remain() > MAX_TRANSFER_LEN {
+            return_errno_with_message!(Errno::EINVAL, "the I2C message is too long");
+        }
+        let buf = reader.collect()?;
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        self.adapter.transfer(&mut [I2cMsg::Write {
+            addr: self.addr(),
+            buf: &buf,
+        }])?;
+        Ok(buf.len())
+    }
+
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        match cmd {
+            IoctlCmd::I2C_SLAVE | IoctlCmd::I2C_SLAVE_FORCE => {
+                if arg > 0x7f {
+                    return_errno_with_message!(Errno::EINVAL, "invalid I2C address");
+                }
+                self.addr.store(arg as u16, Ordering::Relaxed);
+            }
+            IoctlCmd::I2C_TENBIT => {
+                if arg != 0 {
+                    return_errno_with_message!(Errno::EINVAL, "10-bit addresses are not supported");
+                }
+            }
+            IoctlCmd::I2C_FUNCS => current_userspace!().write_val(arg, &self.functionality())?,
+            IoctlCmd::I2C_RDWR => return self.rdwr(arg),
+            IoctlCmd::I2C_SMBUS => return self.smbus(arg),
+            _ => return_errno_with_message!(Errno::ENOTTY, "unsupported ioctl"),
+        }
+        Ok(0)
+    }
+}
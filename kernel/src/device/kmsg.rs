@@ -0,0 +1,208 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The kernel log device, i.e., `/dev/kmsg`.
+//!
+//! Each read returns one entry of the kernel log buffer, in the format of
+//! `<prio>,<seq>,<timestamp in us>,<flags>;<text>\n`. If the entries are overwritten before
+//! being read, the read fails with `EPIPE` and the reader skips to the oldest entry. Each write
+//! logs a message, whose priority can be specified by a `<prio>` prefix.
+//!
+//! Reference: <https://www.kernel.org/doc/Documentation/ABI/testing/dev-kmsg>
+
+use alloc::format;
+
+use aster_logger::{LogEntry, LogReadError, LOG_LINE_MAX, LOG_USER, MESSAGE_LOGLEVEL_DEFAULT};
+use spin::Once;
+
+use crate::{
+    events::IoEvents,
+    fs::{
+        device::{Device, DeviceId, DeviceType},
+        inode_handle::FileIo,
+        utils::StatusFlags,
+    },
+    prelude::*,
+    process::signal::{PollHandle, Pollable, Pollee},
+};
+
+static POLLEE: Once<Pollee> = Once::new();
+
+pub(super) fn init() {
+    POLLEE.call_once(Pollee::new);
+    aster_logger::register_notifier(|| {
+        if let Some(pollee) = POLLEE.get() {
+            pollee.notify(IoEvents::IN);
+        }
+    });
+}
+
+/// Waits until `try_read` does not fail with `EAGAIN`.
+///
+/// `try_read` is retried whenever new entries are logged.
+pub(crate) fn wait_for_log_entries<F, R>(try_read: F) -> Result<R>
+where
+    F: FnMut() -> Result<R>,
+{
+    struct LogBuffer;
+
+    impl Pollable for LogBuffer {
+        fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
+            if let Some(poller) = poller {
+                POLLEE.get().unwrap().register_poller(poller, mask);
+            }
+            IoEvents::empty()
+        }
+    }
+
+    LogBuffer.wait_events(IoEvents::IN, None, try_read)
+}
+
+pub struct Kmsg;
+
+impl Device for Kmsg {
+    fn type_(&self) -> DeviceType {
+        DeviceType::CharDevice
+    }
+
+    fn id(&self) -> DeviceId {
+        DeviceId::new(1, 11)
+    }
+
+    fn open(&self) -> Result<Option<Arc<dyn FileIo>>> {
+        Ok(Some(Arc::new(KmsgFile {
+            seq: Mutex::new(aster_logger::first_seq()),
+        })))
+    }
+}
+
+impl Pollable for Kmsg {
+    fn poll(&self, mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        let events = IoEvents::OUT;
+        events & mask
+    }
+}
+
+impl FileIo for Kmsg {
+    fn read(&self, _writer: &mut VmWriter) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the kernel log device is not opened");
+    }
+
+    fn write(&self, reader: &mut VmReader) -> Result<usize> {
+        write_message(reader)
+    }
+}
+
+/// An opened `/dev/kmsg`.
+struct KmsgFile {
+    /// The sequence number of the next entry to read
+    seq: Mutex<u64>,
+}
+
+impl KmsgFile {
+    fn try_read(&self, writer: &mut VmWriter) -> Result<usize> {
+        let mut seq = self.seq.lock();
+        let entry = match aster_logger::read_entry(*seq) {
+            Ok(entry) => entry,
+            Err(LogReadError::Lost(first_seq)) => {
+                *seq = first_seq;
+                return_errno_with_message!(Errno::EPIPE, "the log entries have been overwritten");
+            }
+            Err(LogReadError::NotYet) => {
+                return_errno_with_message!(Errno::EAGAIN, "there are no new log entries")
+            }
+        };
+
+        let record = format_kmsg_record(&entry);
+        if writer.avail() < record.len() {
+            return_errno_with_message!(Errno::EINVAL, "the buffer is too small for the entry");
+        }
+        writer.write_fallible(&mut VmReader::from(record.as_bytes()))?;
+        *seq = entry.seq + 1;
+        Ok(record.len())
+    }
+}
+
+impl Pollable for KmsgFile {
+    fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
+        if let Some(poller) = poller {
+            POLLEE.get().unwrap().register_poller(poller, mask);
+        }
+
+        let mut events = IoEvents::OUT;
+        if *self.seq.lock() < aster_logger::next_seq() {
+            events |= IoEvents::IN;
+        }
+        events & mask
+    }
+}
+
+impl FileIo for KmsgFile {
+    fn read(&self, writer: &mut VmWriter) -> Result<usize> {
+        self.read_with_flags(writer, StatusFlags::empty())
+    }
+
+    fn write(&self, reader: &mut VmReader) -> Result<usize> {
+        write_message(reader)
+    }
+
+    fn read_with_flags(&self, writer: &mut VmWriter, status_flags: StatusFlags) -> Result<usize> {
+        if status_flags.contains(StatusFlags::O_NONBLOCK) {
+            self.try_read(writer)
+        } else {
+            wait_for_log_entries(|| self.try_read(writer))
+        }
+    }
+}
+
+/// Formats an entry as a record read from `/dev/kmsg`.
+fn format_kmsg_record(entry: &LogEntry) -> String {
+    let mut record = format!(
+        "{},{},{},-;",
+        entry.prio,
+        entry.seq,
+        entry.timestamp.as_micros()
+    );
+    // The non-printable characters are escaped, so each record is exactly one line.
+    for ch in entry.text.chars() {
+        if ch == '\\' || (ch.is_ascii_control() && ch != '\t') {
+            record.push_str(&format!("\\x{:02x}", ch as u32));
+        } else {
+            record.push(ch);
+        }
+    }
+    record.push('\n');
+    record
+}
+
+/// Logs a message written by the user.
+fn write_message(reader: &mut VmReader) -> Result<usize> {
+    let len = reader.remain();
+    let mut buf = vec![0u8; len.min(LOG_LINE_MAX)];
+    reader.read_fallible(&mut VmWriter::from(buf.as_mut_slice()))?;
+
+    let text = String::from_utf8_lossy(&buf);
+    let (prio, message) = parse_prio(&text);
+    aster_logger::log_message(prio, message.trim_end_matches('\n'));
+
+    Ok(len)
+}
+
+/// Parses the `<prio>` prefix of a message.
+///
+/// Like Linux, the users cannot log messages with the kernel facility, so the messages without
+/// a facility are logged with the user facility.
+fn parse_prio(text: &str) -> (u8, &str) {
+    let mut prio = MESSAGE_LOGLEVEL_DEFAULT;
+    let mut message = text;
+    if let Some((prefix, rest)) = text.strip_prefix('<').and_then(|text| text.split_once('>')) {
+        if let Ok(value) = prefix.parse::<u8>() {
+            prio = value;
+            message = rest;
+        }
+    }
+
+    if prio >> 3 == 0 {
+        prio |= LOG_USER << 3;
+    }
+    (prio, message)
+}
@@ -0,0 +1,605 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The loop devices, i.e., `/dev/loopN` and `/dev/loop-control`.
+//!
+//! A loop device is a block device whose sectors are stored in a regular file (the
+//! backing file), so that disk images can be mounted without a real disk. The devices
+//! are bound to their backing files and configured with the `LOOP_*` ioctls of Linux,
+//! which are used by `losetup` and `mount -o loop`.
+//!
+//! Each loop device is also registered as a block device named `loopN`, and its
+//! requests are handled by a kernel thread that reads and writes the backing file.
+
+use alloc::format;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use aster_block::{
+    bio::{Bio, BioEnqueueError, BioStatus, BioType, SubmittedBio},
+    id::Sid,
+    request_queue::{BioRequest, BioRequestSingleQueue},
+    BlockDeviceMeta, BLOCK_SIZE, SECTOR_SIZE,
+};
+use ostd::task::Task;
+
+use crate::{
+    events::IoEvents,
+    fs::{
+        device::{add_node, delete_node, Device, DeviceId, DeviceType},
+        file_handle::FileLike,
+        file_table::{get_file_fast, FileDesc},
+        inode_handle::FileIo,
+        utils::{InodeType, IoctlCmd},
+    },
+    prelude::*,
+    process::signal::{PollHandle, Pollable},
+};
+
+/// The major number of the loop devices.
+const LOOP_MAJOR: u32 = 7;
+
+/// The number of loop devices created at boot time.
+const NR_INITIAL_LOOP_DEVICES: u32 = 8;
+
+/// The upper limit of the number of loop devices.
+const MAX_LOOP_DEVICES: u32 = 256;
+
+/// The length of the file name in `struct loop_info64`.
+const LO_NAME_SIZE: usize = 64;
+
+/// The length of the encryption key in `struct loop_info64`.
+const LO_KEY_SIZE: usize = 32;
+
+static LOOP_DEVICES: Mutex<BTreeMap<u32, Arc<LoopDevice>>> = Mutex::new(BTreeMap::new());
+
+/// `struct loop_info64` in Linux.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct LoopInfo64 {
+    #[expect(dead_code)]
+    device: u64,
+    #[expect(dead_code)]
+    inode: u64,
+    #[expect(dead_code)]
+    rdevice: u64,
+    offset: u64,
+    size_limit: u64,
+    #[expect(dead_code)]
+    number: u32,
+    encrypt_type: u32,
+    _encrypt_key_size: u32,
+    flags: u32,
+    file_name: [u8; LO_NAME_SIZE],
+    _crypt_name: [u8; LO_NAME_SIZE],
+    _encrypt_key: [u8; LO_KEY_SIZE],
+    _init: [u64; 2],
+}
+
+/// `struct loop_config` in Linux, which is used by `LOOP_CONFIGURE`.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct LoopConfig {
+    fd: u32,
+    block_size: u32,
+    info: LoopInfo64,
+    _reserved: [u64; 8],
+}
+
+bitflags! {
+    struct LoopFlags: u32 {
+        const READ_ONLY = 1 << 0;
+        /// Detach the backing file when the device is closed for the last time.
+        ///
+        /// The flag is kept for `LOOP_GET_STATUS64`, but the devices are only
+        /// detached with `LOOP_CLR_FD`.
+        const AUTOCLEAR = 1 << 2;
+        /// Scan the partitions when the backing file is attached.
+        const PARTSCAN  = 1 << 3;
+        const DIRECT_IO = 1 << 4;
+    }
+}
+
+impl LoopFlags {
+    /// The flags that can be changed with `LOOP_SET_STATUS64`.
+    const SETTABLE: Self = Self::AUTOCLEAR.union(Self::PARTSCAN);
+}
+
+/// The backing file of a loop device and how it is mapped.
+struct Backing {
+    file: Arc<dyn FileLike>,
+    /// The offset of the first sector in the backing file.
+    offset: u64,
+    /// The upper limit of the size of the device in bytes, or zero if unlimited.
+    size_limit: u64,
+    flags: LoopFlags,
+    file_name: [u8; LO_NAME_SIZE],
+    /// The names of the partitions of the device registered to `aster_block`.
+    partitions: Vec<String>,
+}
+
+impl Backing {
+    /// Computes the number of sectors of the device from the size of the backing file.
+    fn nr_sectors(&self) -> usize {
+        let file_size = self.file.metadata().size as u64;
+        let mut size = file_size.saturating_sub(self.offset);
+        if self.size_limit != 0 {
+            size = size.min(self.size_limit);
+        }
+        (size / SECTOR_SIZE as u64) as usize
+    }
+
+    fn file_offset(&self, sid: Sid) -> usize {
+        (self.offset + sid.to_offset() as u64) as usize
+    }
+
+    fn read(&self, bio: &SubmittedBio) -> Result<()> {
+        let mut offset = self.file_offset(bio.sid_range().start);
+        for segment in bio.segments() {
+            // The memory is accessed by the CPU directly, since the segments of reads are
+            // mapped for the device to write only.
+            let dma_slice = segment.inner_dma_slice();
+            let mut writer = dma_slice.stream().segment().writer().to_fallible();
+            writer.skip(dma_slice.offset()).limit(dma_slice.nbytes());
+
+            let mut pos = offset;
+            while writer.has_avail() {
+                let len = self.file.read_at(pos, &mut writer)?;
+                if len == 0 {
+                    // The backing file may be shorter than the last sector.
+                    writer
+                        .fill_zeros(writer.avail())
+                        .map_err(|(err, _)| Error::from(err))?;
+                    break;
+                }
+                pos += len;
+            }
+            offset += dma_slice.nbytes();
+        }
+        Ok(())
+    }
+
+    fn write(&self, bio: &SubmittedBio) -> Result<()> {
+        let mut offset = self.file_offset(bio.sid_range().start);
+        for segment in bio.segments() {
+            // The memory is accessed by the CPU directly, since the segments of writes are
+            // mapped for the device to read only.
+            let dma_slice = segment.inner_dma_slice();
+            let mut reader = dma_slice.stream().segment().reader().to_fallible();
+            reader.skip(dma_slice.offset()).limit(dma_slice.nbytes());
+
+            while reader.has_remain() {
+                let len = self.file.write_at(offset, &mut reader)?;
+                if len == 0 {
+                    return_errno_with_message!(Errno::ENOSPC, "the backing file cannot grow");
+                }
+                offset += len;
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<()> {
+        self.file.as_inode_or_err()?.dentry().sync_data()
+    }
+
+    fn info(&self, index: u32) -> LoopInfo64 {
+        let metadata = self.file.metadata();
+        let mut info = LoopInfo64::new_zeroed();
+        info.device = metadata.dev;
+        info.inode = metadata.ino;
+        info.rdevice = metadata.rdev;
+        info.offset = self.offset;
+        info.size_limit = self.size_limit;
+        info.number = index;
+        info.flags = self.flags.bits();
+        info.file_name = self.file_name;
+        info
+    }
+}
+
+/// A loop device, which corresponds to `/dev/loopN` in the file system.
+pub struct LoopDevice {
+    index: u32,
+    queue: BioRequestSingleQueue,
+    backing: Mutex<Option<Backing>>,
+    nr_sectors: AtomicUsize,
+    is_removed: AtomicBool,
+}
+
+impl Debug for LoopDevice {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("LoopDevice")
+            .field("index", &self.index)
+            .field("nr_sectors", &self.nr_sectors.load(Ordering::Relaxed))
+            .finish_non_exhaustive()
+    }
+}
+
+impl LoopDevice {
+    fn new(index: u32) -> Arc<Self> {
+        let device = Arc::new(Self {
+            index,
+            queue: BioRequestSingleQueue::new(),
+            backing: Mutex::new(None),
+            nr_sectors: AtomicUsize::new(0),
+            is_removed: AtomicBool::new(false),
+        });
+
+        let cloned_device = device.clone();
+        let task_fn = move || {
+            while !cloned_device.is_removed.load(Ordering::Acquire) {
+                cloned_device.handle_requests();
+            }
+        };
+        crate::ThreadOptions::new(task_fn).spawn();
+
+        device
+    }
+
+    fn name(&self) -> String {
+        format!("loop{}", self.index)
+    }
+
+    fn is_bound(&self) -> bool {
+        self.backing.lock().is_some()
+    }
+
+    fn handle_requests(&self) {
+        let request = self.queue.dequeue();
+        let backing = self.backing.lock();
+        let Some(backing) = backing.as_ref() else {
+            // The flushes sent to a removed device to wake up this thread end up here.
+            let status = match request.type_() {
+                BioType::Flush => BioStatus::Complete,
+                _ => BioStatus::IoError,
+            };
+            complete_all(&request, status);
+            return;
+        };
+
+        let nr_sectors = self.nr_sectors.load(Ordering::Relaxed) as u64;
+        for bio in request.bios() {
+            let result = match bio.type_() {
+                _ if bio.sid_range().end.to_raw() > nr_sectors => {
+                    Err(Error::with_message(Errno::EIO, "the bio is out of range"))
+                }
+                BioType::Read => backing.read(bio),
+                BioType::Write if backing.flags.contains(LoopFlags::READ_ONLY) => {
+                    Err(Error::with_message(Errno::EROFS, "the device is read-only"))
+                }
+                BioType::Write => backing.write(bio),
+                BioType::Flush => backing.flush(),
+                BioType::Discard => {
+                    bio.complete(BioStatus::NotSupported);
+                    continue;
+                }
+            };
+            let status = match result {
+                Ok(()) => BioStatus::Complete,
+                Err(err) => {
+                    warn!("[loop{}]: {:?} failed: {:?}", self.index, bio.type_(), err);
+                    BioStatus::IoError
+                }
+            };
+            bio.complete(status);
+        }
+    }
+
+    /// Binds the device to the backing file opened as `fd`.
+    fn configure(&self, fd: FileDesc, info: &LoopInfo64) -> Result<()> {
+        if info.encrypt_type != 0 {
+            return_errno_with_message!(Errno::EINVAL, "loop encryption is not supported");
+        }
+
+        let file = {
+            let current_task = Task::current().unwrap();
+            let mut file_table = current_task
+                .as_thread_local()
+                .unwrap()
+                .file_table()
+                .borrow_mut();
+            get_file_fast!(&mut file_table, fd).into_owned()
+        };
+        if file.metadata().type_ != InodeType::File {
+            return_errno_with_message!(Errno::EINVAL, "the backing file is not a regular file");
+        }
+
+        let mut flags = LoopFlags::from_bits_truncate(info.flags);
+        if !file.access_mode().is_writable() {
+            flags |= LoopFlags::READ_ONLY;
+        }
+        let mut file_name = [0u8; LO_NAME_SIZE];
+        if info.file_name[0] != 0 {
+            file_name = info.file_name;
+        } else {
+            let path = file.as_inode_or_err()?.dentry().abs_path();
+            let len = path.len().min(LO_NAME_SIZE - 1);
+            file_name[..len].copy_from_slice(&path.as_bytes()[..len]);
+        }
+        file_name[LO_NAME_SIZE - 1] = 0;
+
+        {
+            let mut backing = self.backing.lock();
+            if backing.is_some() {
+                return_errno_with_message!(Errno::EBUSY, "the loop device is already bound");
+            }
+            let new_backing = Backing {
+                file,
+                offset: info.offset,
+                size_limit: info.size_limit,
+                flags,
+                file_name,
+                partitions: Vec::new(),
+            };
+            self.nr_sectors
+                .store(new_backing.nr_sectors(), Ordering::Relaxed);
+            *backing = Some(new_backing);
+        }
+
+        // The lock must not be held while scanning, since the partition tables are read
+        // by the request thread.
+        if flags.contains(LoopFlags::PARTSCAN) {
+            let partitions = aster_block::partition::scan_partitions(&self.name());
+            if let Some(backing) = self.backing.lock().as_mut() {
+                backing.partitions = partitions;
+            }
+        }
+        Ok(())
+    }
+
+    /// Unbinds the device from the backing file.
+    ///
+    /// The requests that are not handled yet will fail.
+    fn clear(&self) -> Result<()> {
+        let mut backing = self.backing.lock();
+        let Some(old_backing) = backing.take() else {
+            return_errno_with_message!(Errno::ENXIO, "the loop device is not bound");
+        };
+        self.nr_sectors.store(0, Ordering::Relaxed);
+        for partition in old_backing.partitions {
+            aster_block::unregister_device(&partition);
+        }
+        Ok(())
+    }
+
+    fn set_status(&self, info: &LoopInfo64) -> Result<()> {
+        if info.encrypt_type != 0 {
+            return_errno_with_message!(Errno::EINVAL, "loop encryption is not supported");
+        }
+
+        let mut backing = self.backing.lock();
+        let Some(backing) = backing.as_mut() else {
+            return_errno_with_message!(Errno::ENXIO, "the loop device is not bound");
+        };
+        let new_flags = LoopFlags::from_bits_truncate(info.flags);
+        backing.flags = (backing.flags - LoopFlags::SETTABLE) | (new_flags & LoopFlags::SETTABLE);
+        backing.offset = info.offset;
+        backing.size_limit = info.size_limit;
+        backing.file_name = info.file_name;
+        backing.file_name[LO_NAME_SIZE - 1] = 0;
+        self.nr_sectors
+            .store(backing.nr_sectors(), Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn get_status(&self) -> Result<LoopInfo64> {
+        let backing = self.backing.lock();
+        let Some(backing) = backing.as_ref() else {
+            return_errno_with_message!(Errno::ENXIO, "the loop device is not bound");
+        };
+        Ok(backing.info(self.index))
+    }
+
+    /// Updates the size of the device after the backing file is resized.
+    fn update_capacity(&self) -> Result<()> {
+        let backing = self.backing.lock();
+        let Some(backing) = backing.as_ref() else {
+            return_errno_with_message!(Errno::ENXIO, "the loop device is not bound");
+        };
+        self.nr_sectors
+            .store(backing.nr_sectors(), Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+fn complete_all(request: &BioRequest, status: BioStatus) {
+    for bio in request.bios() {
+        bio.complete(status);
+    }
+}
+
+impl aster_block::BlockDevice for LoopDevice {
+    fn enqueue(&self, bio: SubmittedBio) -> Result<(), BioEnqueueError> {
+        if bio.type_() != BioType::Flush && self.nr_sectors.load(Ordering::Relaxed) == 0 {
+            return Err(BioEnqueueError::Refused);
+        }
+        self.queue.enqueue(bio)
+    }
+
+    fn metadata(&self) -> BlockDeviceMeta {
+        BlockDeviceMeta {
+            max_nr_segments_per_bio: self.queue.max_nr_segments_per_bio(),
+            nr_sectors: self.nr_sectors.load(Ordering::Relaxed),
+        }
+    }
+
+    fn plug(&self) {
+        self.queue.plug();
+    }
+
+    fn unplug(&self) {
+        self.queue.unplug();
+    }
+}
+
+impl Device for LoopDevice {
+    fn type_(&self) -> DeviceType {
+        DeviceType::BlockDevice
+    }
+
+    fn id(&self) -> DeviceId {
+        DeviceId::new(LOOP_MAJOR, self.index)
+    }
+}
+
+impl Pollable for LoopDevice {
+    fn poll(&self, mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        let events = IoEvents::IN | IoEvents::OUT;
+        events & mask
+    }
+}
+
+impl FileIo for LoopDevice {
+    fn read(&self, _writer: &mut VmWriter) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "cannot read the loop device directly");
+    }
+
+    fn write(&self, _reader: &mut VmReader) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "cannot write the loop device directly");
+    }
+
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        let user_space = current_userspace!();
+        match cmd {
+            IoctlCmd::LOOP_SET_FD => self.configure(arg as FileDesc, &LoopInfo64::new_zeroed())?,
+            IoctlCmd::LOOP_CONFIGURE => {
+                let config: LoopConfig = user_space.read_val(arg)?;
+                check_block_size(config.block_size)?;
+                self.configure(config.fd as FileDesc, &config.info)?;
+            }
+            IoctlCmd::LOOP_CLR_FD => self.clear()?,
+            IoctlCmd::LOOP_SET_STATUS64 => {
+                let info: LoopInfo64 = user_space.read_val(arg)?;
+                self.set_status(&info)?;
+            }
+            IoctlCmd::LOOP_GET_STATUS64 => user_space.write_val(arg, &self.get_status()?)?,
+            IoctlCmd::LOOP_SET_CAPACITY => self.update_capacity()?,
+            IoctlCmd::LOOP_SET_BLOCK_SIZE => check_block_size(arg as u32)?,
+            IoctlCmd::BLKGETSIZE64 => {
+                let size = (self.nr_sectors.load(Ordering::Relaxed) * SECTOR_SIZE) as u64;
+                user_space.write_val(arg, &size)?;
+            }
+            _ => return_errno_with_message!(Errno::ENOTTY, "unsupported ioctl"),
+        }
+        Ok(0)
+    }
+}
+
+/// Checks the logical block size of `LOOP_CONFIGURE` and `LOOP_SET_BLOCK_SIZE`.
+///
+/// The backing file is accessed in bytes, so any valid block size works without
+/// changing how the requests are handled.
+fn check_block_size(block_size: u32) -> Result<()> {
+    let block_size = block_size as usize;
+    if block_size != 0
+        && (!block_size.is_power_of_two() || !(SECTOR_SIZE..=BLOCK_SIZE).contains(&block_size))
+    {
+        return_errno_with_message!(Errno::EINVAL, "invalid block size");
+    }
+    Ok(())
+}
+
+/// Corresponds to `/dev/loop-control` in the file system.
+pub struct LoopControl;
+
+impl Device for LoopControl {
+    fn type_(&self) -> DeviceType {
+        DeviceType::MiscDevice
+    }
+
+    fn id(&self) -> DeviceId {
+        DeviceId::new(10, 237)
+    }
+}
+
+impl Pollable for LoopControl {
+    fn poll(&self, mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        let events = IoEvents::IN | IoEvents::OUT;
+        events & mask
+    }
+}
+
+impl FileIo for LoopControl {
+    fn read(&self, _writer: &mut VmWriter) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "cannot read the loop control device");
+    }
+
+    fn write(&self, _reader: &mut VmReader) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "cannot write the loop control device");
+    }
+
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        let index = match cmd {
+            IoctlCmd::LOOP_CTL_ADD => add_device(arg as u32)?,
+            IoctlCmd::LOOP_CTL_REMOVE => remove_device(arg as u32)?,
+            IoctlCmd::LOOP_CTL_GET_FREE => get_free_device()?,
+            _ => return_errno_with_message!(Errno::ENOTTY, "unsupported ioctl"),
+        };
+        Ok(index as i32)
+    }
+}
+
+fn add_device(index: u32) -> Result<u32> {
+    if index >= MAX_LOOP_DEVICES {
+        return_errno_with_message!(Errno::EINVAL, "the loop device index is too large");
+    }
+
+    let mut devices = LOOP_DEVICES.lock();
+    if devices.contains_key(&index) {
+        return_errno_with_message!(Errno::EEXIST, "the loop device already exists");
+    }
+    let device = LoopDevice::new(index);
+    aster_block::register_device(device.name(), device.clone());
+    add_node(device.clone(), &device.name())?;
+    devices.insert(index, device);
+    Ok(index)
+}
+
+fn remove_device(index: u32) -> Result<u32> {
+    let mut devices = LOOP_DEVICES.lock();
+    let Some(device) = devices.get(&index) else {
+        return_errno_with_message!(Errno::ENODEV, "the loop device does not exist");
+    };
+    if device.is_bound() {
+        return_errno_with_message!(Errno::EBUSY, "the loop device is bound");
+    }
+
+    let device = devices.remove(&index).unwrap();
+    aster_block::unregister_device(&device.name());
+    delete_node(&device.name())?;
+
+    // Wake up the request thread to let it exit.
+    device.is_removed.store(true, Ordering::Release);
+    let bio = Bio::new(BioType::Flush, Sid::new(0), Vec::new(), None);
+    let _ = bio.submit(device.as_ref());
+    Ok(index)
+}
+
+fn get_free_device() -> Result<u32> {
+    let free_index = {
+        let devices = LOOP_DEVICES.lock();
+        if let Some(device) = devices.values().find(|device| !device.is_bound()) {
+            return Ok(device.index);
+        }
+        (0..MAX_LOOP_DEVICES).find(|index| !devices.contains_key(index))
+    };
+    match free_index {
+        Some(index) => add_device(index),
+        None => return_errno_with_message!(Errno::ENOSPC, "too many loop devices"),
+    }
+}
+
+/// Looks up the loop device with the minor number `index`.
+pub(super) fn get_device(index: u32) -> Result<Arc<dyn Device>> {
+    match LOOP_DEVICES.lock().get(&index) {
+        Some(device) => Ok(device.clone()),
+        None => return_errno_with_message!(Errno::ENODEV, "the loop device does not exist"),
+    }
+}
+
+pub(super) fn init() -> Result<()> {
+    add_node(Arc::new(LoopControl), "loop-control")?;
+    for index in 0..NR_INITIAL_LOOP_DEVICES {
+        add_device(index)?;
+    }
+    Ok(())
+}
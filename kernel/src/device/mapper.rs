@@ -0,0 +1,434 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The control device of the device mapper, i.e., `/dev/mapper/control`.
+//!
+//! The mapped devices (see `aster-dm`) are managed with the `DM_*` ioctls of Linux,
+//! which are used by `dmsetup` and `cryptsetup`. Each ioctl takes a `struct dm_ioctl`,
+//! optionally followed by a data area whose format depends on the command.
+
+use core::mem::size_of;
+
+use align_ext::AlignExt;
+use aster_dm::{DmError, MappedDevice, Table, TargetSpec, DM_NAME_LEN, DM_UUID_LEN};
+
+use crate::{
+    events::IoEvents,
+    fs::{
+        device::{Device, DeviceId, DeviceType},
+        inode_handle::FileIo,
+        utils::IoctlCmd,
+    },
+    prelude::*,
+    process::signal::{PollHandle, Pollable},
+};
+
+/// The version of the interface, which is compatible with Linux 4.x.
+const DM_VERSION: [u32; 3] = [4, 27, 0];
+
+/// The major number of the mapped devices.
+const DM_MAJOR: u32 = 253;
+
+/// The upper limit of the size of the ioctl parameters.
+const DM_MAX_DATA_SIZE: usize = 1024 * 1024;
+
+/// The target types, with their versions reported by `DM_LIST_VERSIONS`.
+const DM_TARGET_VERSIONS: &[(&str, [u32; 3])] = &[("crypt", [1, 23, 0]), ("linear", [1, 4, 0])];
+
+/// The length of the target type in `struct dm_target_spec`.
+const DM_MAX_TYPE_NAME: usize = 16;
+
+/// `struct dm_ioctl` in Linux.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct DmIoctl {
+    version: [u32; 3],
+    /// The size of the parameters, including this structure.
+    data_size: u32,
+    /// The offset of the data area from the start of this structure.
+    data_start: u32,
+    target_count: u32,
+    #[expect(dead_code)]
+    open_count: i32,
+    flags: u32,
+    #[expect(dead_code)]
+    event_nr: u32,
+    _padding: u32,
+    #[expect(dead_code)]
+    dev: u64,
+    name: [u8; DM_NAME_LEN],
+    uuid: [u8; DM_UUID_LEN],
+    _data: [u8; 7],
+}
+
+/// `struct dm_target_spec` in Linux, which is followed by the parameter string.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct DmTargetSpec {
+    sector_start: u64,
+    length: u64,
+    #[expect(dead_code)]
+    status: i32,
+    /// The offset of the next target specification.
+    ///
+    /// It is relative to this structure in `DM_TABLE_LOAD`, and relative to the data
+    /// area in `DM_TABLE_STATUS`.
+    next: u32,
+    target_type: [u8; DM_MAX_TYPE_NAME],
+}
+
+bitflags! {
+    struct DmFlags: u32 {
+        const READONLY          = 1 << 0;
+        const SUSPEND           = 1 << 1;
+        const PERSISTENT_DEV    = 1 << 3;
+        /// Return the table instead of the target status in `DM_TABLE_STATUS`.
+        const STATUS_TABLE      = 1 << 4;
+        const ACTIVE_PRESENT    = 1 << 5;
+        const INACTIVE_PRESENT  = 1 << 6;
+        /// The data area is too small for the output.
+        const BUFFER_FULL       = 1 << 8;
+        /// Use the inactive table instead of the active one.
+        const QUERY_INACTIVE_TABLE = 1 << 12;
+    }
+}
+
+/// Corresponds to `/dev/mapper/control` in the file system.
+pub struct DeviceMapperControl;
+
+impl Device for DeviceMapperControl {
+    fn type_(&self) -> DeviceType {
+        DeviceType::MiscDevice
+    }
+
+    fn id(&self) -> DeviceId {
+        DeviceId::new(10, 236)
+    }
+}
+
+impl Pollable for DeviceMapperControl {
+    fn poll(&self, mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        let events = IoEvents::IN | IoEvents::OUT;
+        events & mask
+    }
+}
+
+impl FileIo for DeviceMapperControl {
+    fn read(&self, _writer: &mut VmWriter) -> Result<usize> {
+        return_errno_with_message!(
+            Errno::EINVAL,
+            "cannot read the device mapper control device"
+        );
+    }
+
+    fn write(&self, _reader: &mut VmReader) -> Result<usize> {
+        return_errno_with_message!(
+            Errno::EINVAL,
+            "cannot write the device mapper control device"
+        );
+    }
+
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        match cmd {
+            IoctlCmd::DM_VERSION
+            | IoctlCmd::DM_REMOVE_ALL
+            | IoctlCmd::DM_LIST_DEVICES
+            | IoctlCmd::DM_DEV_CREATE
+            | IoctlCmd::DM_DEV_REMOVE
+            | IoctlCmd::DM_DEV_SUSPEND
+            | IoctlCmd::DM_DEV_STATUS
+            | IoctlCmd::DM_TABLE_LOAD
+            | IoctlCmd::DM_TABLE_CLEAR
+            | IoctlCmd::DM_TABLE_STATUS
+            | IoctlCmd::DM_LIST_VERSIONS => handle_dm_ioctl(cmd, arg),
+            _ => return_errno_with_message!(Errno::ENOTTY, "unsupported ioctl"),
+        }
+    }
+}
+
+impl From<DmError> for Error {
+    fn from(err: DmError) -> Self {
+        match err {
+            DmError::InvalidArgs(msg) => Error::with_message(Errno::EINVAL, msg),
+            DmError::NotFound => Error::with_message(Errno::ENXIO, "the device does not exist"),
+            DmError::Exists => Error::with_message(Errno::EBUSY, "the device already exists"),
+            DmError::Busy => Error::with_message(Errno::EBUSY, "the device is in use"),
+            DmError::NoKey => Error::with_message(Errno::ENOKEY, "the key does not exist"),
+        }
+    }
+}
+
+fn handle_dm_ioctl(cmd: IoctlCmd, arg: usize) -> Result<i32> {
+    let user_space = current_userspace!();
+
+    let mut param: DmIoctl = user_space.read_val(arg)?;
+    if param.version[0] != DM_VERSION[0] {
+        return_errno_with_message!(Errno::EINVAL, "incompatible device mapper version");
+    }
+    let data_size = param.data_size as usize;
+    let data_start = param.data_start as usize;
+    if data_size < size_of::<DmIoctl>() || data_size > DM_MAX_DATA_SIZE {
+        return_errno_with_message!(Errno::EINVAL, "invalid data size");
+    }
+    if data_start < size_of::<DmIoctl>() || data_start > data_size {
+        return_errno_with_message!(Errno::EINVAL, "invalid data start");
+    }
+
+    let mut buf = vec![0u8; data_size];
+    user_space.read_bytes(arg, &mut VmWriter::from(buf.as_mut_slice()))?;
+    let input = &buf[data_start..];
+
+    param.flags &= !DmFlags::BUFFER_FULL.bits();
+    let output = match cmd {
+        IoctlCmd::DM_VERSION => Vec::new(),
+        IoctlCmd::DM_REMOVE_ALL => {
+            let names: Vec<String> = aster_dm::all_devices()
+                .iter()
+                .map(|device| device.name().to_string())
+                .collect();
+            for name in names {
+                // The devices in use are kept.
+                let _ = aster_dm::remove_device(&name);
+            }
+            Vec::new()
+        }
+        IoctlCmd::DM_LIST_DEVICES => list_devices(),
+        IoctlCmd::DM_LIST_VERSIONS => list_versions(),
+        IoctlCmd::DM_DEV_CREATE => {
+            let device = aster_dm::create_device(
+                read_cstr(&param.name, "invalid device name")?,
+                read_cstr(&param.uuid, "invalid device uuid")?,
+            )?;
+            fill_status(&mut param, &device);
+            Vec::new()
+        }
+        IoctlCmd::DM_DEV_REMOVE => {
+            // The device must not be held here, or it would be considered in use.
+            let name = find_device(&param)?.name().to_string();
+            aster_dm::remove_device(&name)?;
+            Vec::new()
+        }
+        IoctlCmd::DM_DEV_SUSPEND => {
+            let device = find_device(&param)?;
+            if param.flags & DmFlags::SUSPEND.bits() != 0 {
+                device.suspend();
+            } else {
+                device.resume();
+            }
+            fill_status(&mut param, &device);
+            Vec::new()
+        }
+        IoctlCmd::DM_DEV_STATUS => {
+            let device = find_device(&param)?;
+            fill_status(&mut param, &device);
+            Vec::new()
+        }
+        IoctlCmd::DM_TABLE_LOAD => {
+            let device = find_device(&param)?;
+            let table = parse_table(input, param.target_count as usize)?;
+            device.load_table(table);
+            fill_status(&mut param, &device);
+            Vec::new()
+        }
+        IoctlCmd::DM_TABLE_CLEAR => {
+            let device = find_device(&param)?;
+            device.clear_inactive_table();
+            fill_status(&mut param, &device);
+            Vec::new()
+        }
+        IoctlCmd::DM_TABLE_STATUS => {
+            let device = find_device(&param)?;
+            fill_status(&mut param, &device);
+            let table = if param.flags & DmFlags::QUERY_INACTIVE_TABLE.bits() != 0 {
+                device.inactive_table()
+            } else {
+                device.active_table()
+            };
+            match table {
+                Some(table) => {
+                    param.target_count = table.targets().count() as u32;
+                    let with_params = param.flags & DmFlags::STATUS_TABLE.bits() != 0;
+                    table_status(&table, with_params)
+                }
+                None => {
+                    param.target_count = 0;
+                    Vec::new()
+                }
+            }
+        }
+        _ => unreachable!(),
+    };
+
+    param.version = DM_VERSION;
+    if data_start + output.len() > data_size {
+        param.flags |= DmFlags::BUFFER_FULL.bits();
+    } else {
+        param.data_size = (data_start + output.len()) as u32;
+        user_space.write_bytes(arg + data_start, &mut VmReader::from(output.as_slice()))?;
+    }
+    user_space.write_val(arg, &param)?;
+
+    Ok(0)
+}
+
+/// Finds the device by the name, or by the UUID if the name is empty.
+fn find_device(param: &DmIoctl) -> Result<Arc<MappedDevice>> {
+    let name = read_cstr(&param.name, "invalid device name")?;
+    let uuid = read_cstr(&param.uuid, "invalid device uuid")?;
+    let device = if !name.is_empty() {
+        aster_dm::get_device(name)
+    } else {
+        aster_dm::get_device_by_uuid(uuid)
+    };
+    device.ok_or_else(|| Error::with_message(Errno::ENXIO, "the device does not exist"))
+}
+
+fn fill_status(param: &mut DmIoctl, device: &MappedDevice) {
+    let active = device.active_table();
+
+    let mut flags = DmFlags::from_bits_truncate(param.flags);
+    flags.remove(
+        DmFlags::SUSPEND | DmFlags::READONLY | DmFlags::ACTIVE_PRESENT | DmFlags::INACTIVE_PRESENT,
+    );
+    flags.set(DmFlags::SUSPEND, device.is_suspended());
+    flags.set(DmFlags::ACTIVE_PRESENT, active.is_some());
+    flags.set(DmFlags::INACTIVE_PRESENT, device.inactive_table().is_some());
+    // Keep the flags that are unknown to `DmFlags` as they are.
+    param.flags = (param.flags & !DmFlags::all().bits()) | flags.bits();
+
+    param.dev = DeviceId::new(DM_MAJOR, device.minor()).into();
+    param.target_count = active.map_or(0, |table| table.targets().count() as u32);
+    param.open_count = 0;
+    param.event_nr = 0;
+    write_cstr(&mut param.name, device.name());
+    write_cstr(&mut param.uuid, device.uuid());
+}
+
+/// Parses the target specifications of `DM_TABLE_LOAD`.
+fn parse_table(data: &[u8], target_count: usize) -> Result<Table> {
+    let mut specs = Vec::with_capacity(target_count);
+    let mut offset = 0;
+
+    for index in 0..target_count {
+        let Some(spec_bytes) = data.get(offset..offset + size_of::<DmTargetSpec>()) else {
+            return_errno_with_message!(Errno::EINVAL, "the target specification is truncated");
+        };
+        let spec = DmTargetSpec::from_bytes(spec_bytes);
+        let params = read_cstr(
+            &data[offset + size_of::<DmTargetSpec>()..],
+            "invalid target parameters",
+        )?;
+        specs.push(TargetSpec {
+            start: spec.sector_start,
+            len: spec.length,
+            type_name: read_cstr(&spec_bytes[24..], "invalid target type")?,
+            params,
+        });
+
+        if index + 1 < target_count {
+            if (spec.next as usize) < size_of::<DmTargetSpec>() {
+                return_errno_with_message!(Errno::EINVAL, "invalid next target specification");
+            }
+            offset += spec.next as usize;
+        }
+    }
+
+    Ok(Table::new(&specs)?)
+}
+
+/// Generates the output of `DM_TABLE_STATUS`.
+///
+/// The target status is always empty, as that of the `linear` and `crypt` targets
+/// in Linux.
+fn table_status(table: &Table, with_params: bool) -> Vec<u8> {
+    let mut output = Vec::new();
+
+    for (start, len, target) in table.targets() {
+        let spec_offset = output.len();
+        output.resize(spec_offset + size_of::<DmTargetSpec>(), 0);
+        if with_params {
+            output.extend_from_slice(target.params().as_bytes());
+        }
+        output.push(0);
+        output.resize(output.len().align_up(8), 0);
+
+        let mut spec = DmTargetSpec {
+            sector_start: start,
+            length: len,
+            status: 0,
+            next: output.len() as u32,
+            target_type: [0; DM_MAX_TYPE_NAME],
+        };
+        write_cstr(&mut spec.target_type, target.type_name());
+        output[spec_offset..spec_offset + size_of::<DmTargetSpec>()]
+            .copy_from_slice(spec.as_bytes());
+    }
+
+    output
+}
+
+/// Generates the output of `DM_LIST_DEVICES`, which is a list of `struct dm_name_list`.
+fn list_devices() -> Vec<u8> {
+    // The size of `struct dm_name_list` without the name, i.e., `dev` and `next`.
+    const HEADER_LEN: usize = 12;
+
+    let devices = aster_dm::all_devices();
+    if devices.is_empty() {
+        return vec![0; HEADER_LEN.align_up(8)];
+    }
+
+    let mut output = Vec::new();
+    for (index, device) in devices.iter().enumerate() {
+        let entry_offset = output.len();
+        let dev: u64 = DeviceId::new(DM_MAJOR, device.minor()).into();
+        output.extend_from_slice(&dev.to_ne_bytes());
+        output.extend_from_slice(&0u32.to_ne_bytes());
+        output.extend_from_slice(device.name().as_bytes());
+        output.push(0);
+        output.resize(output.len().align_up(8), 0);
+
+        if index + 1 < devices.len() {
+            let next = (output.len() - entry_offset) as u32;
+            output[entry_offset + 8..entry_offset + HEADER_LEN]
+                .copy_from_slice(&next.to_ne_bytes());
+        }
+    }
+
+    output
+}
+
+/// Generates the output of `DM_LIST_VERSIONS`, which is a list of `struct dm_target_versions`.
+fn list_versions() -> Vec<u8> {
+    let mut output = Vec::new();
+
+    for (index, (name, version)) in DM_TARGET_VERSIONS.iter().enumerate() {
+        let entry_offset = output.len();
+        output.extend_from_slice(&0u32.to_ne_bytes());
+        for number in version {
+            output.extend_from_slice(&number.to_ne_bytes());
+        }
+        output.extend_from_slice(name.as_bytes());
+        output.push(0);
+        output.resize(output.len().align_up(8), 0);
+
+        if index + 1 < DM_TARGET_VERSIONS.len() {
+            let next = (output.len() - entry_offset) as u32;
+            output[entry_offset..entry_offset + 4].copy_from_slice(&next.to_ne_bytes());
+        }
+    }
+
+    output
+}
+
+/// Reads a null-terminated string from the bytes.
+fn read_cstr<'a>(bytes: &'a [u8], err_msg: &'static str) -> Result<&'a str> {
+    let Some(len) = bytes.iter().position(|byte| *byte == 0) else {
+        return_errno_with_message!(Errno::EINVAL, err_msg);
+    };
+    core::str::from_utf8(&bytes[..len]).map_err(|_| Error::with_message(Errno::EINVAL, err_msg))
+}
+
+/// Writes a null-terminated string into the field, which must be large enough.
+fn write_cstr(field: &mut [u8], s: &str) {
+    field.fill(0);
+    field[..s.len()].copy_from_slice(s.as_bytes());
+}
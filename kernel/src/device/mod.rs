@@ -2,12 +2,19 @@
 
 use cfg_if::cfg_if;
 
+mod evdev;
+mod i2c_dev;
+mod kmsg;
+mod loop_device;
+mod mapper;
 mod null;
 mod pty;
 mod random;
 mod shm;
 pub mod tty;
+mod tun;
 mod urandom;
+mod watchdog;
 mod zero;
 
 cfg_if! {
@@ -18,6 +25,7 @@ cfg_if! {
     }
 }
 
+pub(crate) use kmsg::wait_for_log_entries;
 use ostd::if_tdx_enabled;
 pub use pty::{new_pty_pair, PtyMaster, PtySlave};
 pub use random::Random;
@@ -48,8 +56,20 @@ pub fn init() -> Result<()> {
     add_node(random, "random")?;
     let urandom = Arc::new(urandom::Urandom);
     add_node(urandom, "urandom")?;
+    kmsg::init();
+    add_node(Arc::new(kmsg::Kmsg), "kmsg")?;
     pty::init()?;
     shm::init()?;
+    add_node(Arc::new(mapper::DeviceMapperControl), "mapper/control")?;
+    loop_device::init()?;
+    add_node(Arc::new(tun::TunDevice), "net/tun")?;
+    watchdog::init()?;
+    i2c_dev::init()?;
+    evdev::init()?;
+    #[cfg(target_arch = "riscv64")]
+    if ostd::arch::virt::is_supported() {
+        add_node(Arc::new(crate::kvm::KvmDevice), "kvm")?;
+    }
     Ok(())
 }
 
@@ -70,8 +90,24 @@ pub fn get_device(dev: usize) -> Result<Arc<dyn Device>> {
         (1, 3) => Ok(Arc::new(null::Null)),
         (1, 5) => Ok(Arc::new(zero::Zero)),
         (5, 0) => Ok(Arc::new(tty::TtyDevice)),
+        (5, 1) => Ok(get_n_tty().clone()),
         (1, 8) => Ok(Arc::new(random::Random)),
         (1, 9) => Ok(Arc::new(urandom::Urandom)),
+        (1, 11) => Ok(Arc::new(kmsg::Kmsg)),
+        (10, 236) => Ok(Arc::new(mapper::DeviceMapperControl)),
+        (10, 237) => Ok(Arc::new(loop_device::LoopControl)),
+        (10, 200) => Ok(Arc::new(tun::TunDevice)),
+        #[cfg(target_arch = "riscv64")]
+        (10, 232) if ostd::arch::virt::is_supported() => Ok(Arc::new(crate::kvm::KvmDevice)),
+        (7, index) => loop_device::get_device(index),
+        (10, 130) | (watchdog::WATCHDOG_MAJOR, _) => watchdog::get_device(major, minor),
+        (i2c_dev::I2C_MAJOR, index) => i2c_dev::get_device(index),
+        (13, minor)
+            if (evdev::EVDEV_MINOR_BASE..evdev::EVDEV_MINOR_BASE + evdev::EVDEV_MINORS)
+                .contains(&minor) =>
+        {
+            evdev::get_device(minor)
+        }
         _ => return_errno_with_message!(Errno::EINVAL, "unsupported device"),
     }
 }
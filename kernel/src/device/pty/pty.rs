@@ -1,424 +1,415 @@
 // SPDX-License-Identifier: MPL-2.0
 
 use alloc::format;
+use core::sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering};
 
-use ostd::task::Task;
+use ostd::{sync::LocalIrqDisabled, task::Task};
 
 use crate::{
     current_userspace,
-    device::tty::{line_discipline::LineDiscipline, new_job_control_and_ldisc},
+    device::tty::{
+        driver::TtyDriver,
+        termio::{KernelTermios, CC_C_CHAR, C_IFLAGS},
+        Tty,
+    },
     events::IoEvents,
     fs::{
-        device::{Device, DeviceId, DeviceType},
         devpts::DevPts,
         file_table::FdFlags,
         fs_resolver::FsPath,
         inode_handle::FileIo,
-        utils::{AccessMode, Inode, InodeMode, IoctlCmd},
+        utils::{CreationFlags, Inode, InodeMode, IoctlCmd},
     },
     prelude::*,
     process::{
         posix_thread::{AsPosixThread, AsThreadLocal},
         signal::{PollHandle, Pollable, Pollee},
-        JobControl, Terminal,
     },
     util::ring_buffer::RingBuffer,
 };
 
 const BUFFER_CAPACITY: usize = 4096;
 
-/// Pseudo terminal master.
-/// Internally, it has two buffers.
-/// One is inside ldisc, which is written by master and read by slave,
-/// the other is a ring buffer, which is written by slave and read by master.
-pub struct PtyMaster {
-    ptmx: Arc<dyn Inode>,
-    index: u32,
-    output: Arc<LineDiscipline>,
-    input: SpinLock<RingBuffer<u8>>,
-    job_control: Arc<JobControl>,
-    /// The state of input buffer
+// The control status bytes in the packet mode.
+// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/asm-generic/ioctls.h>
+const TIOCPKT_DATA: u8 = 0;
+const TIOCPKT_FLUSHREAD: u8 = 1;
+const TIOCPKT_FLUSHWRITE: u8 = 2;
+const TIOCPKT_NOSTOP: u8 = 16;
+const TIOCPKT_DOSTOP: u8 = 32;
+
+/// Pseudo terminal slave.
+///
+/// It is a TTY whose input is written by the master and whose output is read by the master.
+pub type PtySlave = Tty<PtyDriver>;
+
+/// The driver of a pseudo terminal slave.
+///
+/// It holds the output of the slave, which is read by the master.
+pub struct PtyDriver {
+    output: SpinLock<RingBuffer<u8>, LocalIrqDisabled>,
+    /// The pollee of the master.
     pollee: Pollee,
-    weak_self: Weak<Self>,
+    /// Whether the slave cannot be opened (see `TIOCSPTLCK`).
+    is_locked: AtomicBool,
+    /// Whether the master reads in the packet mode (see `TIOCPKT`).
+    is_packet_mode: AtomicBool,
+    /// The pending control status (i.e., `TIOCPKT_*`) in the packet mode.
+    packet_status: AtomicU8,
+    /// The number of opened files of the slave.
+    num_slave_files: AtomicUsize,
+    /// Whether all opened files of the slave are closed.
+    is_slave_closed: AtomicBool,
+    is_master_closed: AtomicBool,
 }
 
-impl PtyMaster {
-    pub fn new(ptmx: Arc<dyn Inode>, index: u32) -> Arc<Self> {
-        let (job_control, ldisc) = new_job_control_and_ldisc();
-        Arc::new_cyclic(move |weak_ref| PtyMaster {
-            ptmx,
-            index,
-            output: ldisc,
-            input: SpinLock::new(RingBuffer::new(BUFFER_CAPACITY)),
-            job_control,
+impl PtyDriver {
+    fn new() -> Self {
+        Self {
+            output: SpinLock::new(RingBuffer::new(BUFFER_CAPACITY)),
             pollee: Pollee::new(),
-            weak_self: weak_ref.clone(),
-        })
+            // The slave is locked until the master unlocks it, which is the same as Linux.
+            is_locked: AtomicBool::new(true),
+            is_packet_mode: AtomicBool::new(false),
+            packet_status: AtomicU8::new(0),
+            num_slave_files: AtomicUsize::new(0),
+            is_slave_closed: AtomicBool::new(false),
+            is_master_closed: AtomicBool::new(false),
+        }
     }
 
-    pub fn index(&self) -> u32 {
-        self.index
-    }
+    fn try_read(&self, writer: &mut VmWriter) -> Result<usize> {
+        let mut output = self.output.lock();
+
+        let is_packet_mode = self.is_packet_mode.load(Ordering::Relaxed);
+        if is_packet_mode {
+            let status = self.packet_status.swap(0, Ordering::Relaxed);
+            if status != 0 {
+                if let Err(err) = writer.write_val(&status) {
+                    self.packet_status.fetch_or(status, Ordering::Relaxed);
+                    return Err(err.into());
+                }
+                self.pollee.invalidate();
+                return Ok(1);
+            }
+        }
 
-    pub fn ptmx(&self) -> &Arc<dyn Inode> {
-        &self.ptmx
+        if output.is_empty() {
+            if self.is_slave_closed.load(Ordering::Relaxed) {
+                return_errno_with_message!(Errno::EIO, "the slave is closed");
+            }
+            return_errno_with_message!(Errno::EAGAIN, "the buffer is empty");
+        }
+
+        let read_len = if is_packet_mode {
+            writer.write_val(&TIOCPKT_DATA)?;
+            output.read_fallible(writer)? + 1
+        } else {
+            output.read_fallible(writer)?
+        };
+        self.pollee.invalidate();
+
+        Ok(read_len)
     }
 
-    pub(super) fn slave_push_char(&self, ch: u8) {
-        let mut input = self.input.disable_irq().lock();
-        input.push_overwrite(ch);
-        self.pollee.notify(IoEvents::IN);
+    fn buffer_len(&self) -> usize {
+        self.output.lock().len()
     }
 
-    pub(super) fn slave_poll(
-        &self,
-        mask: IoEvents,
-        mut poller: Option<&mut PollHandle>,
-    ) -> IoEvents {
-        let mut poll_status = IoEvents::empty();
-
-        let poll_in_mask = mask & IoEvents::IN;
-        if !poll_in_mask.is_empty() {
-            let poll_in_status = self.output.poll(poll_in_mask, poller.as_deref_mut());
-            poll_status |= poll_in_status;
-        }
+    fn check_io_events(&self) -> IoEvents {
+        let mut events = IoEvents::OUT;
 
-        let poll_out_mask = mask & IoEvents::OUT;
-        if !poll_out_mask.is_empty() {
-            let poll_out_status = self
-                .pollee
-                .poll_with(poll_out_mask, poller, || self.check_io_events());
-            poll_status |= poll_out_status;
+        if !self.output.lock().is_empty() {
+            events |= IoEvents::IN;
+        }
+        if self.is_packet_mode.load(Ordering::Relaxed)
+            && self.packet_status.load(Ordering::Relaxed) != 0
+        {
+            events |= IoEvents::IN | IoEvents::PRI;
+        }
+        if self.is_slave_closed.load(Ordering::Relaxed) {
+            events |= IoEvents::HUP;
         }
 
-        poll_status
-    }
-
-    pub(super) fn slave_buf_len(&self) -> usize {
-        self.output.buffer_len()
+        events
     }
 
-    fn try_read(&self, writer: &mut VmWriter) -> Result<usize> {
-        let mut input = self.input.disable_irq().lock();
-
-        if input.is_empty() {
-            return_errno_with_message!(Errno::EAGAIN, "the buffer is empty");
+    /// Adds the control status in the packet mode, after clearing the bits in `cleared`.
+    fn add_packet_status(&self, status: u8, cleared: u8) {
+        if !self.is_packet_mode.load(Ordering::Relaxed) {
+            return;
         }
 
-        let read_len = input.read_fallible(writer)?;
-        self.pollee.invalidate();
+        let _ = self
+            .packet_status
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |old| {
+                Some((old & !cleared) | status)
+            });
+        self.pollee.notify(IoEvents::IN | IoEvents::PRI);
+    }
 
-        Ok(read_len)
+    fn set_packet_mode(&self, is_packet_mode: bool) {
+        let _guard = self.output.lock();
+        self.is_packet_mode.store(is_packet_mode, Ordering::Relaxed);
+        self.packet_status.store(0, Ordering::Relaxed);
+        self.pollee.invalidate();
     }
+}
 
-    fn check_io_events(&self) -> IoEvents {
-        let input = self.input.disable_irq().lock();
+impl TtyDriver for PtyDriver {
+    // The same value as the major ID of the PTY slaves in Linux.
+    const DEVICE_MAJOR_ID: u32 = 136;
 
-        if !input.is_empty() {
-            IoEvents::IN | IoEvents::OUT
-        } else {
-            IoEvents::OUT
+    fn push_output(&self, chs: &[u8]) {
+        let mut output = self.output.lock();
+        for ch in chs {
+            output.push_overwrite(*ch);
         }
+        self.pollee.notify(IoEvents::IN);
     }
-}
 
-impl Pollable for PtyMaster {
-    fn poll(&self, mask: IoEvents, mut poller: Option<&mut PollHandle>) -> IoEvents {
-        let mut poll_status = IoEvents::empty();
-
-        let poll_in_mask = mask & IoEvents::IN;
-        if !poll_in_mask.is_empty() {
-            let poll_in_status = self
-                .pollee
-                .poll_with(poll_in_mask, poller.as_deref_mut(), || {
-                    self.check_io_events()
-                });
-            poll_status |= poll_in_status;
-        }
+    fn open(tty: Arc<Tty<Self>>) -> Result<Arc<dyn FileIo>> {
+        {
+            let driver = tty.driver();
+            if driver.is_master_closed.load(Ordering::Relaxed) {
+                return_errno_with_message!(Errno::EIO, "the master is closed");
+            }
+            if driver.is_locked.load(Ordering::Relaxed) {
+                return_errno_with_message!(Errno::EIO, "the slave is locked");
+            }
 
-        let poll_out_mask = mask & IoEvents::OUT;
-        if !poll_out_mask.is_empty() {
-            let poll_out_status = self.output.poll(poll_out_mask, poller);
-            poll_status |= poll_out_status;
+            driver.num_slave_files.fetch_add(1, Ordering::Relaxed);
+            driver.is_slave_closed.store(false, Ordering::Relaxed);
+            driver.pollee.invalidate();
         }
 
-        poll_status
+        Ok(Arc::new(PtySlaveFile(tty)))
     }
-}
 
-impl FileIo for PtyMaster {
-    fn read(&self, writer: &mut VmWriter) -> Result<usize> {
-        if !writer.has_avail() {
-            return Ok(0);
-        }
+    fn is_hung_up(&self) -> bool {
+        self.is_master_closed.load(Ordering::Relaxed)
+    }
 
-        // TODO: deal with nonblocking and timeout
-        self.wait_events(IoEvents::IN, None, || self.try_read(writer))
+    fn flush_output(&self) {
+        self.output.lock().clear();
+        self.pollee.invalidate();
+        self.add_packet_status(TIOCPKT_FLUSHWRITE, 0);
     }
 
-    fn write(&self, reader: &mut VmReader) -> Result<usize> {
-        let buf = reader.collect()?;
-        let write_len = buf.len();
-        let mut input = self.input.lock();
-        for character in buf {
-            self.output.push_char(character, |content| {
-                for byte in content.as_bytes() {
-                    input.push_overwrite(*byte);
-                }
-            });
+    fn on_input_flushed(&self) {
+        self.add_packet_status(TIOCPKT_FLUSHREAD, 0);
+    }
+
+    fn on_termios_changed(&self, old_termios: &KernelTermios, new_termios: &KernelTermios) {
+        let old_flow = has_default_flow_control(old_termios);
+        let new_flow = has_default_flow_control(new_termios);
+        if old_flow == new_flow {
+            return;
         }
 
-        self.pollee.notify(IoEvents::IN);
-        Ok(write_len)
+        let status = if new_flow {
+            TIOCPKT_DOSTOP
+        } else {
+            TIOCPKT_NOSTOP
+        };
+        self.add_packet_status(status, TIOCPKT_DOSTOP | TIOCPKT_NOSTOP);
     }
+}
 
-    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
-        match cmd {
-            IoctlCmd::TCGETS => {
-                let termios = self.output.termios();
-                current_userspace!().write_val(arg, &termios)?;
-                Ok(0)
-            }
-            IoctlCmd::TCSETS => {
-                let termios = current_userspace!().read_val(arg)?;
-                self.output.set_termios(termios);
-                Ok(0)
-            }
-            IoctlCmd::TIOCSPTLCK => {
-                // TODO: lock/unlock pty
-                Ok(0)
-            }
-            IoctlCmd::TIOCGPTN => {
-                let idx = self.index();
-                current_userspace!().write_val(arg, &idx)?;
-                Ok(0)
-            }
-            IoctlCmd::TIOCGPTPEER => {
-                let current_task = Task::current().unwrap();
-                let posix_thread = current_task.as_posix_thread().unwrap();
-                let thread_local = current_task.as_thread_local().unwrap();
-
-                // TODO: deal with open options
-                let slave = {
-                    let slave_name = {
-                        let devpts_path = super::DEV_PTS.get().unwrap().abs_path();
-                        format!("{}/{}", devpts_path, self.index())
-                    };
-
-                    let fs_path = FsPath::try_from(slave_name.as_str())?;
-
-                    let inode_handle = {
-                        let fs = posix_thread.fs().resolver().read();
-                        let flags = AccessMode::O_RDWR as u32;
-                        let mode = (InodeMode::S_IRUSR | InodeMode::S_IWUSR).bits();
-                        fs.open(&fs_path, flags, mode)?
-                    };
-                    Arc::new(inode_handle)
-                };
+/// Returns whether the output can be stopped and started with `^S` and `^Q`.
+fn has_default_flow_control(termios: &KernelTermios) -> bool {
+    termios.c_iflags().contains(C_IFLAGS::IXON)
+        && *termios.get_special_char(CC_C_CHAR::VSTOP) == b'\x13'
+        && *termios.get_special_char(CC_C_CHAR::VSTART) == b'\x11'
+}
 
-                let fd = {
-                    let file_table = thread_local.file_table().borrow();
-                    let mut file_table_locked = file_table.write();
-                    // TODO: deal with the O_CLOEXEC flag
-                    file_table_locked.insert(slave, FdFlags::empty())
-                };
-                Ok(fd)
-            }
-            IoctlCmd::TIOCGWINSZ => {
-                let winsize = self.output.window_size();
-                current_userspace!().write_val(arg, &winsize)?;
-                Ok(0)
-            }
-            IoctlCmd::TIOCSWINSZ => {
-                let winsize = current_userspace!().read_val(arg)?;
-                self.output.set_window_size(winsize);
-                Ok(0)
-            }
-            IoctlCmd::TIOCGPGRP => {
-                let Some(foreground) = self.foreground() else {
-                    return_errno_with_message!(
-                        Errno::ESRCH,
-                        "the foreground process group does not exist"
-                    );
-                };
-                let fg_pgid = foreground.pgid();
-                current_userspace!().write_val(arg, &fg_pgid)?;
-                Ok(0)
-            }
-            IoctlCmd::TIOCSPGRP => {
-                let pgid = {
-                    let pgid: i32 = current_userspace!().read_val(arg)?;
-                    if pgid < 0 {
-                        return_errno_with_message!(Errno::EINVAL, "negative pgid");
-                    }
-                    pgid as u32
-                };
+/// An opened file of the pseudo terminal slave.
+///
+/// The master knows whether the slave is closed by counting the opened files.
+struct PtySlaveFile(Arc<PtySlave>);
 
-                self.set_foreground(&pgid)?;
-                Ok(0)
-            }
-            IoctlCmd::TIOCSCTTY => {
-                self.set_current_session()?;
-                Ok(0)
-            }
-            IoctlCmd::TIOCNOTTY => {
-                self.release_current_session()?;
-                Ok(0)
-            }
-            IoctlCmd::FIONREAD => {
-                let len = self.input.lock().len() as i32;
-                current_userspace!().write_val(arg, &len)?;
-                Ok(0)
-            }
-            _ => Ok(0),
-        }
+impl Pollable for PtySlaveFile {
+    fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
+        self.0.poll(mask, poller)
     }
 }
 
-impl Terminal for PtyMaster {
-    fn arc_self(&self) -> Arc<dyn Terminal> {
-        self.weak_self.upgrade().unwrap() as _
+impl FileIo for PtySlaveFile {
+    fn read(&self, writer: &mut VmWriter) -> Result<usize> {
+        self.0.read(writer)
+    }
+
+    fn write(&self, reader: &mut VmReader) -> Result<usize> {
+        self.0.write(reader)
     }
 
-    fn job_control(&self) -> &JobControl {
-        &self.job_control
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        self.0.ioctl(cmd, arg)
     }
 }
 
-impl Drop for PtyMaster {
+impl Drop for PtySlaveFile {
     fn drop(&mut self) {
-        let fs = self.ptmx.fs();
-        let devpts = fs.downcast_ref::<DevPts>().unwrap();
-
-        let index = self.index;
-        devpts.remove_slave(index);
+        let driver = self.0.driver();
+        if driver.num_slave_files.fetch_sub(1, Ordering::Relaxed) == 1 {
+            driver.is_slave_closed.store(true, Ordering::Relaxed);
+            driver.pollee.notify(IoEvents::HUP);
+        }
     }
 }
 
-pub struct PtySlave {
-    master: Weak<PtyMaster>,
-    job_control: JobControl,
-    weak_self: Weak<Self>,
+/// Pseudo terminal master.
+///
+/// The data written to the master is the input of the slave, which is processed by the line
+/// discipline of the slave. The output of the slave can be read from the master.
+pub struct PtyMaster {
+    ptmx: Arc<dyn Inode>,
+    slave: Arc<PtySlave>,
 }
 
-impl PtySlave {
-    pub fn new(master: &Arc<PtyMaster>) -> Arc<Self> {
-        Arc::new_cyclic(|weak_ref| PtySlave {
-            master: Arc::downgrade(master),
-            job_control: JobControl::new(),
-            weak_self: weak_ref.clone(),
+impl PtyMaster {
+    pub fn new(ptmx: Arc<dyn Inode>, index: u32) -> Arc<Self> {
+        Arc::new(PtyMaster {
+            ptmx,
+            slave: Tty::new(index, PtyDriver::new()),
         })
     }
 
     pub fn index(&self) -> u32 {
-        self.master().index()
-    }
-
-    fn master(&self) -> Arc<PtyMaster> {
-        self.master.upgrade().unwrap()
-    }
-}
-
-impl Device for PtySlave {
-    fn type_(&self) -> DeviceType {
-        DeviceType::CharDevice
+        self.slave.index()
     }
 
-    fn id(&self) -> crate::fs::device::DeviceId {
-        DeviceId::new(88, self.index())
+    pub fn ptmx(&self) -> &Arc<dyn Inode> {
+        &self.ptmx
     }
-}
 
-impl Terminal for PtySlave {
-    fn arc_self(&self) -> Arc<dyn Terminal> {
-        self.weak_self.upgrade().unwrap() as _
+    pub fn slave(&self) -> &Arc<PtySlave> {
+        &self.slave
     }
 
-    fn job_control(&self) -> &JobControl {
-        &self.job_control
+    fn open_slave(&self, flags: u32) -> Result<i32> {
+        let current_task = Task::current().unwrap();
+        let posix_thread = current_task.as_posix_thread().unwrap();
+        let thread_local = current_task.as_thread_local().unwrap();
+
+        let slave = {
+            let slave_name = {
+                let devpts_path = super::DEV_PTS.get().unwrap().abs_path();
+                format!("{}/{}", devpts_path, self.index())
+            };
+
+            let fs_path = FsPath::try_from(slave_name.as_str())?;
+
+            let inode_handle = {
+                let fs = posix_thread.fs().resolver().read();
+                let mode = (InodeMode::S_IRUSR | InodeMode::S_IWUSR).bits();
+                fs.open(&fs_path, flags, mode)?
+            };
+            Arc::new(inode_handle)
+        };
+
+        let fd = {
+            let file_table = thread_local.file_table().borrow();
+            let mut file_table_locked = file_table.write();
+            let fd_flags =
+                if CreationFlags::from_bits_truncate(flags).contains(CreationFlags::O_CLOEXEC) {
+                    FdFlags::CLOEXEC
+                } else {
+                    FdFlags::empty()
+                };
+            file_table_locked.insert(slave, fd_flags)?
+        };
+        Ok(fd)
     }
 }
 
-impl Pollable for PtySlave {
+impl Pollable for PtyMaster {
     fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
-        self.master().slave_poll(mask, poller)
+        let driver = self.slave.driver();
+        driver
+            .pollee
+            .poll_with(mask, poller, || driver.check_io_events())
     }
 }
 
-impl FileIo for PtySlave {
+impl FileIo for PtyMaster {
     fn read(&self, writer: &mut VmWriter) -> Result<usize> {
-        let mut buf = vec![0u8; writer.avail()];
-        self.job_control.wait_until_in_foreground()?;
-        let read_len = self.master().output.read(&mut buf)?;
-        writer.write_fallible(&mut buf.as_slice().into())?;
-        Ok(read_len)
+        if !writer.has_avail() {
+            return Ok(0);
+        }
+
+        // TODO: deal with nonblocking and timeout
+        let driver = self.slave.driver();
+        self.wait_events(IoEvents::IN, None, || driver.try_read(writer))
     }
 
     fn write(&self, reader: &mut VmReader) -> Result<usize> {
         let buf = reader.collect()?;
-        let write_len = buf.len();
-        let master = self.master();
-        for ch in buf {
-            // do we need to add '\r' here?
-            if ch == b'\n' {
-                master.slave_push_char(b'\r');
-                master.slave_push_char(b'\n');
-            } else {
-                master.slave_push_char(ch);
-            }
-        }
-        Ok(write_len)
+        self.slave.push_input(&buf);
+        Ok(buf.len())
     }
 
     fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        let driver = self.slave.driver();
+
         match cmd {
             IoctlCmd::TCGETS
             | IoctlCmd::TCSETS
-            | IoctlCmd::TIOCGPTN
+            | IoctlCmd::TCSETSW
+            | IoctlCmd::TCSETSF
             | IoctlCmd::TIOCGWINSZ
-            | IoctlCmd::TIOCSWINSZ => self.master().ioctl(cmd, arg),
-            IoctlCmd::TIOCGPGRP => {
-                if !self.is_controlling_terminal() {
-                    return_errno_with_message!(Errno::ENOTTY, "slave is not controlling terminal");
-                }
-
-                let Some(foreground) = self.foreground() else {
-                    return_errno_with_message!(
-                        Errno::ESRCH,
-                        "the foreground process group does not exist"
-                    );
-                };
-
-                let fg_pgid = foreground.pgid();
-                current_userspace!().write_val(arg, &fg_pgid)?;
+            | IoctlCmd::TIOCSWINSZ => self.slave.ioctl(cmd, arg),
+            IoctlCmd::TIOCSPTLCK => {
+                let is_locked: i32 = current_userspace!().read_val(arg)?;
+                driver.is_locked.store(is_locked != 0, Ordering::Relaxed);
                 Ok(0)
             }
-            IoctlCmd::TIOCSPGRP => {
-                let pgid = {
-                    let pgid: i32 = current_userspace!().read_val(arg)?;
-                    if pgid < 0 {
-                        return_errno_with_message!(Errno::EINVAL, "negative pgid");
-                    }
-                    pgid as u32
-                };
-
-                self.set_foreground(&pgid)?;
+            IoctlCmd::TIOCGPTLCK => {
+                let is_locked = driver.is_locked.load(Ordering::Relaxed) as i32;
+                current_userspace!().write_val(arg, &is_locked)?;
+                Ok(0)
+            }
+            IoctlCmd::TIOCPKT => {
+                let is_packet_mode: i32 = current_userspace!().read_val(arg)?;
+                driver.set_packet_mode(is_packet_mode != 0);
                 Ok(0)
             }
-            IoctlCmd::TIOCSCTTY => {
-                self.set_current_session()?;
+            IoctlCmd::TIOCGPKT => {
+                let is_packet_mode = driver.is_packet_mode.load(Ordering::Relaxed) as i32;
+                current_userspace!().write_val(arg, &is_packet_mode)?;
                 Ok(0)
             }
-            IoctlCmd::TIOCNOTTY => {
-                self.release_current_session()?;
+            IoctlCmd::TIOCGPTN => {
+                let idx = self.index();
+                current_userspace!().write_val(arg, &idx)?;
                 Ok(0)
             }
+            IoctlCmd::TIOCGPTPEER => self.open_slave(arg as u32),
             IoctlCmd::FIONREAD => {
-                let buffer_len = self.master().slave_buf_len() as i32;
-                current_userspace!().write_val(arg, &buffer_len)?;
+                let len = driver.buffer_len() as i32;
+                current_userspace!().write_val(arg, &len)?;
                 Ok(0)
             }
-            _ => Ok(0),
+            _ => return_errno_with_message!(
+                Errno::ENOTTY,
+                "the ioctl is not supported by pty master"
+            ),
         }
     }
 }
+
+impl Drop for PtyMaster {
+    fn drop(&mut self) {
+        self.slave
+            .driver()
+            .is_master_closed
+            .store(true, Ordering::Relaxed);
+        self.slave.hang_up();
+
+        let fs = self.ptmx.fs();
+        let devpts = fs.downcast_ref::<DevPts>().unwrap();
+
+        let index = self.index();
+        devpts.remove_slave(index);
+    }
+}
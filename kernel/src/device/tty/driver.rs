@@ -1,96 +1,50 @@
 // SPDX-License-Identifier: MPL-2.0
 
-#![expect(dead_code)]
-
-use ostd::mm::{Infallible, VmReader};
-use spin::Once;
-
-use crate::{
-    device::tty::{get_n_tty, Tty},
-    prelude::*,
-};
-
-pub static TTY_DRIVER: Once<Arc<TtyDriver>> = Once::new();
-
-pub(super) fn init() {
-    for (_, device) in aster_console::all_devices() {
-        device.register_callback(&console_input_callback)
-    }
-    let tty_driver = Arc::new(TtyDriver::new());
-    // FIXME: install n_tty into tty_driver?
-    let n_tty = get_n_tty();
-    tty_driver.install(n_tty.clone());
-    TTY_DRIVER.call_once(|| tty_driver);
-}
-
-pub struct TtyDriver {
-    ttys: SpinLock<Vec<Arc<Tty>>>,
-}
-
-impl TtyDriver {
-    pub const fn new() -> Self {
-        Self {
-            ttys: SpinLock::new(Vec::new()),
-        }
-    }
-
-    /// Return the tty device in driver's internal table.
-    pub fn lookup(&self, index: usize) -> Result<Arc<Tty>> {
-        let ttys = self.ttys.disable_irq().lock();
-        // Return the tty device corresponding to idx
-        if index >= ttys.len() {
-            return_errno_with_message!(Errno::ENODEV, "lookup failed. No tty device");
-        }
-        let tty = ttys[index].clone();
-        drop(ttys);
+use super::{termio::KernelTermios, Tty};
+use crate::{fs::inode_handle::FileIo, prelude::*};
+
+/// A TTY driver.
+///
+/// A TTY driver transmits the output characters of a [`Tty`] to the underlying device, which
+/// can be a hardware device (e.g., the serial port or the virtio console) or a software one
+/// (e.g., the master side of a pseudoterminal).
+pub trait TtyDriver: Send + Sync + 'static {
+    /// The major device ID of the TTYs.
+    ///
+    /// The minor device ID of a TTY is its index.
+    const DEVICE_MAJOR_ID: u32;
+
+    /// Pushes the output characters to the device.
+    ///
+    /// This method may be called in the interrupt context (e.g., to echo the input
+    /// characters), so it must not sleep.
+    fn push_output(&self, chs: &[u8]);
+
+    /// Opens the TTY.
+    ///
+    /// This method is called each time the TTY device is opened. The returned file serves the
+    /// subsequent file operations.
+    fn open(tty: Arc<Tty<Self>>) -> Result<Arc<dyn FileIo>>
+    where
+        Self: Sized,
+    {
         Ok(tty)
     }
 
-    /// Install a new tty into the driver's internal tables.
-    pub fn install(self: &Arc<Self>, tty: Arc<Tty>) {
-        tty.set_driver(Arc::downgrade(self));
-        self.ttys.disable_irq().lock().push(tty);
+    /// Returns whether the TTY is hung up.
+    ///
+    /// A hung-up TTY reads nothing after the buffered characters are consumed, and fails
+    /// all writes with `EIO`.
+    fn is_hung_up(&self) -> bool {
+        false
     }
 
-    /// remove a new tty into the driver's internal tables.
-    pub fn remove(&self, index: usize) -> Result<()> {
-        let mut ttys = self.ttys.disable_irq().lock();
-        if index >= ttys.len() {
-            return_errno_with_message!(Errno::ENODEV, "lookup failed. No tty device");
-        }
-        let removed_tty = ttys.remove(index);
-        removed_tty.set_driver(Weak::new());
-        drop(ttys);
-        Ok(())
-    }
-
-    pub fn push_char(&self, ch: u8) {
-        // FIXME: should the char send to all ttys?
-        for tty in &*self.ttys.disable_irq().lock() {
-            tty.push_char(ch);
-        }
-    }
-}
+    /// Discards the output characters that are not transmitted yet.
+    fn flush_output(&self) {}
 
-impl Default for TtyDriver {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
-fn console_input_callback(mut reader: VmReader<Infallible>) {
-    let tty_driver = get_tty_driver();
-    while reader.remain() > 0 {
-        let ch = reader.read_val().unwrap();
-        tty_driver.push_char(ch);
-    }
-}
-
-fn serial_input_callback(item: u8) {
-    let tty_driver = get_tty_driver();
-    tty_driver.push_char(item);
-}
+    /// Notifies the driver that the input characters of the TTY are discarded.
+    fn on_input_flushed(&self) {}
 
-fn get_tty_driver() -> &'static TtyDriver {
-    TTY_DRIVER.get().unwrap()
+    /// Notifies the driver that the termios of the TTY is changed.
+    fn on_termios_changed(&self, _old_termios: &KernelTermios, _new_termios: &KernelTermios) {}
 }
@@ -1,420 +1,492 @@
 // SPDX-License-Identifier: MPL-2.0
 
-#![expect(unused_variables)]
+use core::time::Duration;
 
-use alloc::format;
-
-use ostd::{
-    sync::LocalIrqDisabled,
-    trap::{disable_local, in_interrupt_context},
-};
-
-use super::termio::{KernelTermios, WinSize, CC_C_CHAR};
+use super::termio::{KernelTermios, WinSize, CC_C_CHAR, C_IFLAGS, C_LFLAGS, C_OFLAGS};
 use crate::{
-    events::IoEvents,
     prelude::*,
     process::signal::{
-        constants::{SIGINT, SIGQUIT},
-        signals::kernel::KernelSignal,
-        PollHandle, Pollable, Pollee,
+        constants::{SIGINT, SIGQUIT, SIGTSTP},
+        sig_num::SigNum,
     },
-    thread::work_queue::{submit_work_item, work_item::WorkItem, WorkPriority},
     util::ring_buffer::RingBuffer,
 };
 
 // This implementation refers the implementation of linux
-// https://elixir.bootlin.com/linux/latest/source/include/linux/tty_ldisc.h
-
-const BUFFER_CAPACITY: usize = 4096;
+// https://elixir.bootlin.com/linux/latest/source/drivers/tty/n_tty.c
 
-pub type LdiscSignalSender = Arc<dyn Fn(KernelSignal) + Send + Sync + 'static>;
+pub(super) const BUFFER_CAPACITY: usize = 4096;
 
-// Lock ordering to prevent deadlock (circular dependencies):
-// 1. `termios`
-// 2. `current_line`
-// 3. `read_buffer`
-// 4. `work_item_para`
+/// The line discipline of a TTY (i.e., `N_TTY` in Linux).
+///
+/// It turns the input characters into the characters to read according to the termios. In
+/// the canonical mode, the input is assembled into lines that can be edited. The line
+/// discipline also decides which characters are echoed and which signals are generated.
 pub struct LineDiscipline {
-    /// Current line
-    current_line: SpinLock<CurrentLine, LocalIrqDisabled>,
-    /// The read buffer
-    read_buffer: SpinLock<RingBuffer<u8>, LocalIrqDisabled>,
-    /// Termios
-    termios: SpinLock<KernelTermios, LocalIrqDisabled>,
-    /// Windows size
-    winsize: SpinLock<WinSize, LocalIrqDisabled>,
-    /// Pollee
-    pollee: Pollee,
-    /// Used to send signal for foreground processes, when some char comes.
-    send_signal: LdiscSignalSender,
-    /// Work item
-    work_item: Arc<WorkItem>,
-    /// Parameters used by a work item.
-    work_item_para: Arc<SpinLock<LineDisciplineWorkPara, LocalIrqDisabled>>,
+    /// The line that is being edited in the canonical mode.
+    current_line: CurrentLine,
+    /// The characters that are ready to be read.
+    read_buffer: RingBuffer<u8>,
+    termios: KernelTermios,
+    winsize: WinSize,
 }
 
-pub struct CurrentLine {
-    buffer: RingBuffer<u8>,
+struct CurrentLine {
+    buffer: Vec<u8>,
 }
 
-impl Default for CurrentLine {
-    fn default() -> Self {
+impl CurrentLine {
+    fn new() -> Self {
         Self {
-            buffer: RingBuffer::new(BUFFER_CAPACITY),
+            buffer: Vec::with_capacity(BUFFER_CAPACITY),
         }
     }
-}
 
-impl CurrentLine {
-    /// Reads all bytes inside current line and clear current line
-    pub fn drain(&mut self) -> Vec<u8> {
-        let mut ret = vec![0u8; self.buffer.len()];
-        self.buffer.pop_slice(ret.as_mut_slice()).unwrap();
-        ret
+    fn push_char(&mut self, ch: u8) {
+        self.buffer.push(ch);
     }
 
-    pub fn push_char(&mut self, char: u8) {
-        // What should we do if line is full?
-        debug_assert!(!self.is_full());
-        self.buffer.push_overwrite(char);
+    fn pop_char(&mut self) -> Option<u8> {
+        self.buffer.pop()
     }
 
-    pub fn backspace(&mut self) {
-        let _ = self.buffer.pop();
+    fn last_char(&self) -> Option<u8> {
+        self.buffer.last().copied()
     }
 
-    pub fn is_full(&self) -> bool {
-        self.buffer.is_full()
+    fn drain(&mut self) -> impl Iterator<Item = u8> + '_ {
+        self.buffer.drain(..)
     }
 
-    pub fn is_empty(&self) -> bool {
-        self.buffer.is_empty()
+    fn clear(&mut self) {
+        self.buffer.clear();
     }
-}
 
-impl Pollable for LineDiscipline {
-    fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
-        self.pollee
-            .poll_with(mask, poller, || self.check_io_events())
+    fn is_full(&self) -> bool {
+        // One byte is reserved for the line terminator.
+        self.buffer.len() + 1 >= BUFFER_CAPACITY
     }
 }
 
 impl LineDiscipline {
-    /// Creates a new line discipline
-    pub fn new(send_signal: LdiscSignalSender) -> Arc<Self> {
-        Arc::new_cyclic(move |line_ref: &Weak<LineDiscipline>| {
-            let line_discipline = line_ref.clone();
-            let work_item = WorkItem::new(Box::new(move || {
-                if let Some(line_discipline) = line_discipline.upgrade() {
-                    line_discipline.send_signal_after();
-                }
-            }));
-            Self {
-                current_line: SpinLock::new(CurrentLine::default()),
-                read_buffer: SpinLock::new(RingBuffer::new(BUFFER_CAPACITY)),
-                termios: SpinLock::new(KernelTermios::default()),
-                winsize: SpinLock::new(WinSize::default()),
-                pollee: Pollee::new(),
-                send_signal,
-                work_item,
-                work_item_para: Arc::new(SpinLock::new(LineDisciplineWorkPara::new())),
-            }
-        })
+    /// Creates a new line discipline.
+    pub fn new() -> Self {
+        Self {
+            current_line: CurrentLine::new(),
+            read_buffer: RingBuffer::new(BUFFER_CAPACITY),
+            termios: KernelTermios::default(),
+            winsize: WinSize::default(),
+        }
     }
 
-    /// Pushes a char to the line discipline
-    pub fn push_char<F2: FnMut(&str)>(&self, ch: u8, echo_callback: F2) {
-        let termios = self.termios.lock();
-
-        let ch = if termios.contains_icrnl() && ch == b'\r' {
-            b'\n'
-        } else {
-            ch
+    /// Pushes an input character to the line discipline.
+    ///
+    /// If the character generates a signal, `signal_callback` is called with the signal
+    /// number. The characters to echo are passed to `echo_callback`.
+    pub fn push_char<F1, F2>(&mut self, ch: u8, mut signal_callback: F1, mut echo_callback: F2)
+    where
+        F1: FnMut(SigNum),
+        F2: FnMut(&[u8]),
+    {
+        let Some(ch) = self.map_input_char(ch) else {
+            return;
         };
+        let lflags = self.termios.c_lflags();
+        let is_echo = lflags.contains(C_LFLAGS::ECHO);
 
-        if self.may_send_signal(&termios, ch) {
-            submit_work_item(self.work_item.clone(), WorkPriority::High);
-            // CBREAK mode may require the character to be outputted, so just go ahead.
-        }
-
-        // Typically, a tty in raw mode does not echo. But the tty can also be in a CBREAK mode,
-        // with ICANON closed and ECHO opened.
-        if termios.contain_echo() {
-            self.output_char(ch, &termios, echo_callback);
+        if lflags.contains(C_LFLAGS::ISIG) {
+            if let Some(sig_num) = self.signal_of(ch) {
+                if !lflags.contains(C_LFLAGS::NOFLSH) {
+                    self.drain_input();
+                }
+                if is_echo {
+                    self.echo_char(ch, &mut echo_callback);
+                }
+                signal_callback(sig_num);
+                return;
+            }
         }
 
         // Raw mode
-        if !termios.is_canonical_mode() {
-            self.read_buffer.lock().push_overwrite(ch);
-            self.pollee.notify(IoEvents::IN);
+        if !lflags.contains(C_LFLAGS::ICANON) {
+            self.read_buffer.push_overwrite(ch);
+            if is_echo {
+                self.echo_char(ch, &mut echo_callback);
+            }
             return;
         }
 
         // Canonical mode
 
-        if ch == *termios.get_special_char(CC_C_CHAR::VKILL) {
-            // Erase current line
-            self.current_line.lock().drain();
+        if self.termios.is_special_char(ch, CC_C_CHAR::VERASE) {
+            if let Some(erased) = self.current_line.pop_char() {
+                if is_echo {
+                    self.echo_erase(erased, ch, &mut echo_callback);
+                }
+            }
+            return;
+        }
+
+        if self.termios.is_special_char(ch, CC_C_CHAR::VKILL) {
+            self.kill_line(ch, is_echo, &mut echo_callback);
+            return;
         }
 
-        if ch == *termios.get_special_char(CC_C_CHAR::VERASE) {
-            // Type backspace
-            let mut current_line = self.current_line.lock();
-            if !current_line.is_empty() {
-                current_line.backspace();
-            }
+        if lflags.contains(C_LFLAGS::IEXTEN) && self.termios.is_special_char(ch, CC_C_CHAR::VWERASE)
+        {
+            self.erase_word(ch, is_echo, &mut echo_callback);
+            return;
         }
 
-        if is_line_terminator(ch, &termios) {
-            // If a new line is met, all bytes in current_line will be moved to read_buffer
-            let mut current_line = self.current_line.lock();
-            current_line.push_char(ch);
-            let current_line_chars = current_line.drain();
-            for char in current_line_chars {
-                self.read_buffer.lock().push_overwrite(char);
-                self.pollee.notify(IoEvents::IN);
+        if is_line_terminator(ch, &self.termios) {
+            if is_eof(ch, &self.termios) {
+                // The EOF character is never echoed.
+            } else if is_echo {
+                self.echo_char(ch, &mut echo_callback);
+            } else if ch == b'\n' && lflags.contains(C_LFLAGS::ECHONL) {
+                self.echo_char(ch, &mut echo_callback);
             }
+
+            // If a line terminator is met, the current line becomes ready to read.
+            self.current_line.push_char(ch);
+            for ch in self.current_line.drain() {
+                self.read_buffer.push_overwrite(ch);
+            }
+            return;
         }
 
-        if is_printable_char(ch) {
-            // Printable character
-            self.current_line.lock().push_char(ch);
+        if self.current_line.is_full() {
+            // The character is dropped since the line is too long.
+            return;
+        }
+        self.current_line.push_char(ch);
+        if is_echo {
+            self.echo_char(ch, &mut echo_callback);
         }
     }
 
-    fn may_send_signal(&self, termios: &KernelTermios, ch: u8) -> bool {
-        if !termios.is_canonical_mode() || !termios.contains_isig() {
-            return false;
-        }
+    /// Maps the input character according to the input flags.
+    ///
+    /// Returns `None` if the character should be ignored.
+    fn map_input_char(&self, ch: u8) -> Option<u8> {
+        let iflags = self.termios.c_iflags();
 
-        let signal = match ch {
-            ch if ch == *termios.get_special_char(CC_C_CHAR::VINTR) => KernelSignal::new(SIGINT),
-            ch if ch == *termios.get_special_char(CC_C_CHAR::VQUIT) => KernelSignal::new(SIGQUIT),
-            _ => return false,
+        let ch = if iflags.contains(C_IFLAGS::ISTRIP) {
+            ch & 0x7f
+        } else {
+            ch
         };
 
-        if in_interrupt_context() {
-            // `kernel_signal()` may cause sleep, so only construct parameters here.
-            self.work_item_para.lock().kernel_signal = Some(signal);
+        match ch {
+            b'\r' if iflags.contains(C_IFLAGS::IGNCR) => None,
+            b'\r' if iflags.contains(C_IFLAGS::ICRNL) => Some(b'\n'),
+            b'\n' if iflags.contains(C_IFLAGS::INLCR) => Some(b'\r'),
+            ch => Some(ch),
+        }
+    }
+
+    fn signal_of(&self, ch: u8) -> Option<SigNum> {
+        if self.termios.is_special_char(ch, CC_C_CHAR::VINTR) {
+            Some(SIGINT)
+        } else if self.termios.is_special_char(ch, CC_C_CHAR::VQUIT) {
+            Some(SIGQUIT)
+        } else if self.termios.is_special_char(ch, CC_C_CHAR::VSUSP) {
+            Some(SIGTSTP)
         } else {
-            (self.send_signal)(signal);
+            None
         }
+    }
 
-        true
+    fn echo_char<F: FnMut(&[u8])>(&self, ch: u8, echo_callback: &mut F) {
+        match ch {
+            b'\n' => process_output(&self.termios, b"\n", echo_callback),
+            ch if is_ctrl_char(ch) && self.termios.contains_echo_ctl() => {
+                echo_callback(&[b'^', ch ^ 0x40]);
+            }
+            ch => echo_callback(&[ch]),
+        }
     }
 
-    fn check_io_events(&self) -> IoEvents {
-        let buffer = self.read_buffer.lock();
+    /// Echoes the erasure of the `erased` character, which is erased by `erase_char`.
+    fn echo_erase<F: FnMut(&[u8])>(&self, erased: u8, erase_char: u8, echo_callback: &mut F) {
+        if !self.termios.c_lflags().contains(C_LFLAGS::ECHOE) {
+            self.echo_char(erase_char, echo_callback);
+            return;
+        }
 
-        if !buffer.is_empty() {
-            IoEvents::IN
+        // A control character may have been echoed as two characters.
+        let width = if is_ctrl_char(erased) && self.termios.contains_echo_ctl() {
+            2
         } else {
-            IoEvents::empty()
+            1
+        };
+        for _ in 0..width {
+            echo_callback(b"\x08 \x08");
         }
     }
 
-    /// Sends a signal later. The signal will be handled by a work queue.
-    fn send_signal_after(&self) {
-        if let Some(signal) = self.work_item_para.lock().kernel_signal.take() {
-            (self.send_signal)(signal);
-        };
-    }
+    fn kill_line<F: FnMut(&[u8])>(&mut self, kill_char: u8, is_echo: bool, echo_callback: &mut F) {
+        let lflags = self.termios.c_lflags();
+        let erases_chars = lflags.contains(C_LFLAGS::ECHOE | C_LFLAGS::ECHOK | C_LFLAGS::ECHOKE);
 
-    // TODO: respect output flags
-    fn output_char<F: FnMut(&str)>(&self, ch: u8, termios: &KernelTermios, mut echo_callback: F) {
-        match ch {
-            b'\n' => echo_callback("\n"),
-            b'\r' => echo_callback("\r\n"),
-            ch if ch == *termios.get_special_char(CC_C_CHAR::VERASE) => {
-                // write a space to overwrite current character
-                let backspace: &str = core::str::from_utf8(b"\x08 \x08").unwrap();
-                echo_callback(backspace);
+        if is_echo && !erases_chars {
+            self.echo_char(kill_char, echo_callback);
+            if lflags.contains(C_LFLAGS::ECHOK) {
+                self.echo_char(b'\n', echo_callback);
             }
-            ch if is_printable_char(ch) => print!("{}", char::from(ch)),
-            ch if is_ctrl_char(ch) && termios.contains_echo_ctl() => {
-                let ctrl_char = format!("^{}", get_printable_char(ch));
-                echo_callback(&ctrl_char);
+        }
+
+        while let Some(erased) = self.current_line.pop_char() {
+            if is_echo && erases_chars {
+                self.echo_erase(erased, kill_char, echo_callback);
             }
-            item => {}
         }
     }
 
-    pub fn read(&self, buf: &mut [u8]) -> Result<usize> {
-        self.wait_events(IoEvents::IN, None, || self.try_read(buf))
-    }
+    fn erase_word<F: FnMut(&[u8])>(
+        &mut self,
+        werase_char: u8,
+        is_echo: bool,
+        echo_callback: &mut F,
+    ) {
+        let mut is_in_word = false;
+        while let Some(last) = self.current_line.last_char() {
+            let is_blank = last == b' ' || last == b'\t';
+            if is_in_word && is_blank {
+                break;
+            }
+            is_in_word |= !is_blank;
 
-    /// Reads all bytes buffered to `dst`.
-    ///
-    /// This method returns the actual read length.
-    fn try_read(&self, dst: &mut [u8]) -> Result<usize> {
-        let (vmin, vtime) = {
-            let termios = self.termios.lock();
-            let vmin = *termios.get_special_char(CC_C_CHAR::VMIN);
-            let vtime = *termios.get_special_char(CC_C_CHAR::VTIME);
-            (vmin, vtime)
-        };
-        let read_len = {
-            let len = self.read_buffer.lock().len();
-            let max_read_len = len.min(dst.len());
-            if vmin == 0 && vtime == 0 {
-                // poll read
-                self.poll_read(dst)
-            } else if vmin > 0 && vtime == 0 {
-                // block read
-                self.block_read(dst, vmin)?
-            } else if vmin == 0 && vtime > 0 {
-                todo!()
-            } else if vmin > 0 && vtime > 0 {
-                todo!()
-            } else {
-                unreachable!()
+            self.current_line.pop_char();
+            if is_echo {
+                self.echo_erase(last, werase_char, echo_callback);
             }
-        };
-        self.pollee.invalidate();
-        Ok(read_len)
+        }
     }
 
-    /// Reads bytes from `self` to `dst`, returning the actual bytes read.
+    /// Tries to read characters to `dst`.
     ///
-    /// If no bytes are available, this method returns 0 immediately.
-    fn poll_read(&self, dst: &mut [u8]) -> usize {
-        let termios = self.termios.lock();
-        let mut buffer = self.read_buffer.lock();
-        let len = buffer.len();
-        let max_read_len = len.min(dst.len());
-        if max_read_len == 0 {
-            return 0;
-        }
-        let mut read_len = 0;
-        for dst_i in dst.iter_mut().take(max_read_len) {
-            if let Some(next_char) = buffer.pop() {
-                if termios.is_canonical_mode() {
-                    // canonical mode, read until meet new line
-                    if is_line_terminator(next_char, &termios) {
-                        // The eof should not be read
-                        if !is_eof(next_char, &termios) {
-                            *dst_i = next_char;
-                            read_len += 1;
-                        }
-                        break;
-                    } else {
-                        *dst_i = next_char;
+    /// Returns the number of characters read, or `EAGAIN` if the reader needs to wait for
+    /// more characters.
+    pub fn try_read(&mut self, dst: &mut [u8]) -> Result<usize> {
+        if self.termios.is_canonical_mode() {
+            if self.read_buffer.is_empty() {
+                return_errno_with_message!(Errno::EAGAIN, "no line is ready to read");
+            }
+
+            // Read until a line terminator is met.
+            let mut read_len = 0;
+            while read_len < dst.len() {
+                let Some(ch) = self.read_buffer.pop() else {
+                    break;
+                };
+                if is_line_terminator(ch, &self.termios) {
+                    // The EOF character should not be read.
+                    if !is_eof(ch, &self.termios) {
+                        dst[read_len] = ch;
                         read_len += 1;
                     }
-                } else {
-                    // raw mode
-                    // FIXME: avoid additional bound check
-                    *dst_i = next_char;
-                    read_len += 1;
+                    break;
                 }
-            } else {
-                break;
+                dst[read_len] = ch;
+                read_len += 1;
             }
+            return Ok(read_len);
+        }
+
+        let vmin = *self.termios.get_special_char(CC_C_CHAR::VMIN) as usize;
+        let vtime = *self.termios.get_special_char(CC_C_CHAR::VTIME);
+        // FIXME: If both `VMIN` and `VTIME` are positive, `VTIME` should be an inter-character
+        // timer. For now, the read returns as soon as any character is available.
+        let min_len = match (vmin, vtime) {
+            (0, 0) => 0,
+            (0, _) => 1,
+            (vmin, 0) => vmin,
+            (_, _) => 1,
+        }
+        .min(dst.len());
+
+        if self.read_buffer.len() < min_len {
+            return_errno_with_message!(Errno::EAGAIN, "not enough characters to read");
         }
 
-        read_len
+        let read_len = self.read_buffer.len().min(dst.len());
+        self.read_buffer.pop_slice(&mut dst[..read_len]).unwrap();
+        Ok(read_len)
     }
 
-    /// Reads bytes from `self` into `dst`,
-    /// returning the actual number of bytes read.
-    ///
-    /// # Errors
-    ///
-    /// If the available bytes are fewer than `min(dst.len(), vmin)`,
-    /// this method returns [`Errno::EAGAIN`].
-    pub fn block_read(&self, dst: &mut [u8], vmin: u8) -> Result<usize> {
-        let _guard = disable_local();
-        let buffer_len = self.read_buffer.lock().len();
-        if buffer_len >= dst.len() {
-            return Ok(self.poll_read(dst));
+    /// Returns the timeout of reads, which is specified by `VTIME` in the non-canonical mode
+    /// when `VMIN` is zero.
+    pub fn read_timeout(&self) -> Option<Duration> {
+        if self.termios.is_canonical_mode() || *self.termios.get_special_char(CC_C_CHAR::VMIN) != 0
+        {
+            return None;
         }
-        if buffer_len < vmin as usize {
-            return_errno!(Errno::EAGAIN);
+
+        match *self.termios.get_special_char(CC_C_CHAR::VTIME) {
+            0 => None,
+            // `VTIME` is in tenths of a second.
+            vtime => Some(Duration::from_millis(vtime as u64 * 100)),
         }
-        Ok(self.poll_read(&mut dst[..buffer_len]))
     }
 
-    /// Returns whether there is buffered data
-    pub fn is_empty(&self) -> bool {
-        self.read_buffer.lock().len() == 0
+    /// Returns whether there are characters to read.
+    pub fn is_readable(&self) -> bool {
+        !self.read_buffer.is_empty()
     }
 
     pub fn termios(&self) -> KernelTermios {
-        *self.termios.lock()
+        self.termios
     }
 
-    pub fn set_termios(&self, termios: KernelTermios) {
-        *self.termios.lock() = termios;
+    pub fn set_termios(&mut self, termios: KernelTermios) {
+        let was_canonical = self.termios.is_canonical_mode();
+        self.termios = termios;
+
+        // The characters that are being edited become ready to read in the raw mode.
+        if was_canonical && !termios.is_canonical_mode() {
+            for ch in self.current_line.drain() {
+                self.read_buffer.push_overwrite(ch);
+            }
+        }
     }
 
-    pub fn drain_input(&self) {
-        self.current_line.lock().drain();
-        self.read_buffer.lock().clear();
-        self.pollee.invalidate();
+    /// Discards all input characters, including those in the line being edited.
+    pub fn drain_input(&mut self) {
+        self.current_line.clear();
+        self.read_buffer.clear();
     }
 
     pub fn buffer_len(&self) -> usize {
-        self.read_buffer.lock().len()
+        self.read_buffer.len()
     }
 
     pub fn window_size(&self) -> WinSize {
-        *self.winsize.lock()
+        self.winsize
     }
 
-    pub fn set_window_size(&self, winsize: WinSize) {
-        *self.winsize.lock() = winsize;
+    pub fn set_window_size(&mut self, winsize: WinSize) {
+        self.winsize = winsize;
     }
 }
 
-fn is_line_terminator(item: u8, termios: &KernelTermios) -> bool {
-    if item == b'\n'
-        || item == *termios.get_special_char(CC_C_CHAR::VEOF)
-        || item == *termios.get_special_char(CC_C_CHAR::VEOL)
-    {
-        return true;
+impl Default for LineDiscipline {
+    fn default() -> Self {
+        Self::new()
     }
+}
 
-    if termios.contains_iexten() && item == *termios.get_special_char(CC_C_CHAR::VEOL2) {
-        return true;
+/// Processes the output characters according to the output flags of `termios`.
+///
+/// The processed characters are passed to `output_callback`, which may be called multiple
+/// times.
+pub fn process_output<F: FnMut(&[u8])>(
+    termios: &KernelTermios,
+    buf: &[u8],
+    output_callback: &mut F,
+) {
+    let oflags = termios.c_oflags();
+    if !oflags.contains(C_OFLAGS::OPOST) {
+        output_callback(buf);
+        return;
     }
 
-    false
+    let mut start = 0;
+    for (i, ch) in buf.iter().enumerate() {
+        let replaced: &[u8] = match *ch {
+            b'\n' if oflags.contains(C_OFLAGS::ONLCR) => b"\r\n",
+            b'\r' if oflags.contains(C_OFLAGS::OCRNL) => b"\n",
+            _ => continue,
+        };
+        if start < i {
+            output_callback(&buf[start..i]);
+        }
+        output_callback(replaced);
+        start = i + 1;
+    }
+    if start < buf.len() {
+        output_callback(&buf[start..]);
+    }
 }
 
-fn is_eof(ch: u8, termios: &KernelTermios) -> bool {
-    ch == *termios.get_special_char(CC_C_CHAR::VEOF)
+fn is_line_terminator(ch: u8, termios: &KernelTermios) -> bool {
+    if ch == b'\n'
+        || termios.is_special_char(ch, CC_C_CHAR::VEOF)
+        || termios.is_special_char(ch, CC_C_CHAR::VEOL)
+    {
+        return true;
+    }
+
+    termios.contains_iexten() && termios.is_special_char(ch, CC_C_CHAR::VEOL2)
 }
 
-fn is_printable_char(ch: u8) -> bool {
-    (0x20..0x7f).contains(&ch)
+fn is_eof(ch: u8, termios: &KernelTermios) -> bool {
+    termios.is_special_char(ch, CC_C_CHAR::VEOF)
 }
 
 fn is_ctrl_char(ch: u8) -> bool {
-    if ch == b'\r' || ch == b'\n' {
+    if ch == b'\t' || ch == b'\n' {
         return false;
     }
 
-    (0..0x20).contains(&ch)
+    ch < 0x20 || ch == 0x7f
 }
 
-fn get_printable_char(ctrl_char: u8) -> char {
-    debug_assert!(is_ctrl_char(ctrl_char));
-    char::from_u32((ctrl_char + b'A' - 1) as u32).unwrap()
-}
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    fn push_str(ldisc: &mut LineDiscipline, chs: &[u8]) -> (Vec<u8>, Vec<SigNum>) {
+        let mut echoed = Vec::new();
+        let mut signals = Vec::new();
+        for ch in chs {
+            ldisc.push_char(
+                *ch,
+                |sig_num| signals.push(sig_num),
+                |chs| echoed.extend_from_slice(chs),
+            );
+        }
+        (echoed, signals)
+    }
 
-enum PolleeType {
-    Add,
-    Del,
-}
+    #[ktest]
+    fn canonical_editing() {
+        let mut ldisc = LineDiscipline::new();
+        let mut buf = [0u8; 16];
 
-struct LineDisciplineWorkPara {
-    kernel_signal: Option<KernelSignal>,
-}
+        let (echoed, _) = push_str(&mut ldisc, b"ab\x7fc");
+        assert_eq!(echoed, b"ab\x08 \x08c");
+        assert!(ldisc.try_read(&mut buf).is_err());
 
-impl LineDisciplineWorkPara {
-    fn new() -> Self {
-        Self {
-            kernel_signal: None,
-        }
+        push_str(&mut ldisc, b"\r");
+        assert_eq!(ldisc.try_read(&mut buf).unwrap(), 3);
+        assert_eq!(&buf[..3], b"ac\n");
+
+        // The EOF character terminates the line but is not read.
+        push_str(&mut ldisc, b"x\x15yz\x04");
+        assert_eq!(ldisc.try_read(&mut buf).unwrap(), 2);
+        assert_eq!(&buf[..2], b"yz");
+    }
+
+    #[ktest]
+    fn signals() {
+        let mut ldisc = LineDiscipline::new();
+        let mut buf = [0u8; 16];
+
+        // The signal character discards the pending input.
+        let (echoed, signals) = push_str(&mut ldisc, b"abc\x03\x1an\n");
+        assert_eq!(echoed, b"abc^C^Zn\r\n");
+        assert_eq!(signals, [SIGINT, SIGTSTP]);
+        assert_eq!(ldisc.try_read(&mut buf).unwrap(), 2);
+        assert_eq!(&buf[..2], b"n\n");
+
+        // A special character is disabled if it is zero.
+        let mut termios = ldisc.termios();
+        *termios.get_special_char_mut(CC_C_CHAR::VINTR) = 0;
+        ldisc.set_termios(termios);
+        let (_, signals) = push_str(&mut ldisc, b"\x03");
+        assert!(signals.is_empty());
     }
 }
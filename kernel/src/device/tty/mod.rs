@@ -1,11 +1,12 @@
 // SPDX-License-Identifier: MPL-2.0
 
-#![expect(dead_code)]
+use ostd::{sync::LocalIrqDisabled, trap::in_interrupt_context};
 
-use ostd::early_print;
-use spin::Once;
-
-use self::{driver::TtyDriver, line_discipline::LineDiscipline};
+use self::{
+    driver::TtyDriver,
+    line_discipline::{process_output, LineDiscipline, BUFFER_CAPACITY},
+    termio::C_LFLAGS,
+};
 use crate::{
     current_userspace,
     events::IoEvents,
@@ -16,84 +17,209 @@ use crate::{
     },
     prelude::*,
     process::{
-        signal::{signals::kernel::KernelSignal, PollHandle, Pollable},
-        JobControl, Process, Terminal,
+        signal::{
+            constants::{SIGCONT, SIGHUP, SIGWINCH},
+            sig_num::SigNum,
+            signals::kernel::KernelSignal,
+            PollHandle, Pollable, Pollee,
+        },
+        JobControl, Terminal,
     },
+    thread::work_queue::{submit_work_item, work_item::WorkItem, WorkPriority},
 };
 
 mod device;
 pub mod driver;
 pub mod line_discipline;
+mod n_tty;
 pub mod termio;
 
 pub use device::TtyDevice;
-
-static N_TTY: Once<Arc<Tty>> = Once::new();
+pub use n_tty::{get_n_tty, open_ntty_as_controlling_terminal, ConsoleDriver};
 
 pub(super) fn init() {
-    let name = CString::new("console").unwrap();
-    let tty = Tty::new(name);
-    N_TTY.call_once(|| tty);
-    driver::init();
+    n_tty::init();
 }
 
-pub struct Tty {
-    /// tty_name
-    name: CString,
-    /// line discipline
-    ldisc: Arc<LineDiscipline>,
-    job_control: Arc<JobControl>,
-    /// driver
-    driver: SpinLock<Weak<TtyDriver>>,
+/// A TTY.
+///
+/// The input characters are processed by the line discipline before they can be read, and the
+/// output characters are transmitted by the [`TtyDriver`]. A TTY can be the controlling
+/// terminal of a session, in which case it supports job control.
+pub struct Tty<D> {
+    index: u32,
+    driver: D,
+    ldisc: SpinLock<LineDiscipline, LocalIrqDisabled>,
+    job_control: JobControl,
+    pollee: Pollee,
+    /// The signal that is generated in the interrupt context and will be sent by `signal_work`.
+    pending_signal: SpinLock<Option<SigNum>, LocalIrqDisabled>,
+    signal_work: Arc<WorkItem>,
     weak_self: Weak<Self>,
 }
 
-impl Tty {
-    pub fn new(name: CString) -> Arc<Self> {
-        let (job_control, ldisc) = new_job_control_and_ldisc();
-        Arc::new_cyclic(move |weak_ref| Tty {
-            name,
-            ldisc,
-            job_control,
-            driver: SpinLock::new(Weak::new()),
-            weak_self: weak_ref.clone(),
+impl<D: TtyDriver> Tty<D> {
+    pub fn new(index: u32, driver: D) -> Arc<Self> {
+        Arc::new_cyclic(|weak_ref: &Weak<Self>| {
+            let signal_work = {
+                let tty = weak_ref.clone();
+                WorkItem::new(Box::new(move || {
+                    if let Some(tty) = tty.upgrade() {
+                        tty.send_pending_signal();
+                    }
+                }))
+            };
+            Self {
+                index,
+                driver,
+                ldisc: SpinLock::new(LineDiscipline::new()),
+                job_control: JobControl::new(),
+                pollee: Pollee::new(),
+                pending_signal: SpinLock::new(None),
+                signal_work,
+                weak_self: weak_ref.clone(),
+            }
         })
     }
 
-    pub fn set_driver(&self, driver: Weak<TtyDriver>) {
-        *self.driver.disable_irq().lock() = driver;
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    pub fn driver(&self) -> &D {
+        &self.driver
+    }
+
+    /// Pushes the input characters to the TTY.
+    ///
+    /// The characters are processed by the line discipline, which may echo them and generate
+    /// signals for the foreground process group.
+    ///
+    /// This method may be called in the interrupt context.
+    pub fn push_input(&self, chs: &[u8]) {
+        let mut signal = None;
+        let is_readable = {
+            let mut ldisc = self.ldisc.lock();
+            for ch in chs {
+                ldisc.push_char(
+                    *ch,
+                    |sig_num| signal = Some(sig_num),
+                    |echoed| self.driver.push_output(echoed),
+                );
+            }
+            ldisc.is_readable()
+        };
+
+        self.update_pollee(is_readable);
+
+        if let Some(sig_num) = signal {
+            if in_interrupt_context() {
+                // Sending signals may sleep, so the work is deferred to a work queue.
+                *self.pending_signal.lock() = Some(sig_num);
+                submit_work_item(self.signal_work.clone(), WorkPriority::High);
+            } else {
+                self.send_signal(sig_num);
+            }
+        }
+    }
+
+    /// Hangs up the TTY.
+    ///
+    /// The foreground process group receives `SIGHUP` and `SIGCONT`. The driver should report
+    /// the TTY as hung up (see [`TtyDriver::is_hung_up`]) before calling this method.
+    pub fn hang_up(&self) {
+        self.pollee
+            .notify(IoEvents::IN | IoEvents::OUT | IoEvents::ERR | IoEvents::HUP);
+
+        if let Some(foreground) = self.job_control.foreground() {
+            foreground.broadcast_signal(KernelSignal::new(SIGHUP));
+            foreground.broadcast_signal(KernelSignal::new(SIGCONT));
+        }
+    }
+
+    fn update_pollee(&self, is_readable: bool) {
+        if is_readable {
+            self.pollee.notify(IoEvents::IN);
+        } else {
+            self.pollee.invalidate();
+        }
+    }
+
+    fn send_pending_signal(&self) {
+        let pending_signal = self.pending_signal.lock().take();
+        if let Some(sig_num) = pending_signal {
+            self.send_signal(sig_num);
+        }
+    }
+
+    /// Sends a signal to the foreground process group.
+    fn send_signal(&self, sig_num: SigNum) {
+        if let Some(foreground) = self.job_control.foreground() {
+            foreground.broadcast_signal(KernelSignal::new(sig_num));
+        }
+    }
+
+    fn try_read(&self, buf: &mut [u8]) -> Result<usize> {
+        let res = self.ldisc.lock().try_read(buf);
+        self.pollee.invalidate();
+
+        match res {
+            Err(err) if err.error() == Errno::EAGAIN && self.driver.is_hung_up() => Ok(0),
+            res => res,
+        }
     }
 
-    pub fn push_char(&self, ch: u8) {
-        // FIXME: Use `early_print` to avoid calling virtio-console.
-        // This is only a workaround
-        self.ldisc
-            .push_char(ch, |content| early_print!("{}", content))
+    fn check_io_events(&self) -> IoEvents {
+        if self.driver.is_hung_up() {
+            IoEvents::IN | IoEvents::OUT | IoEvents::ERR | IoEvents::HUP
+        } else if self.ldisc.lock().is_readable() {
+            IoEvents::IN | IoEvents::OUT
+        } else {
+            IoEvents::OUT
+        }
     }
 }
 
-impl Pollable for Tty {
+impl<D: TtyDriver> Pollable for Tty<D> {
     fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
-        self.ldisc.poll(mask, poller)
+        self.pollee
+            .poll_with(mask, poller, || self.check_io_events())
     }
 }
 
-impl FileIo for Tty {
+impl<D: TtyDriver> FileIo for Tty<D> {
     fn read(&self, writer: &mut VmWriter) -> Result<usize> {
-        let mut buf = vec![0; writer.avail()];
-        self.job_control.wait_until_in_foreground()?;
-        let read_len = self.ldisc.read(buf.as_mut_slice())?;
-        writer.write_fallible(&mut buf.as_slice().into())?;
+        self.job_control.check_background_read()?;
+
+        if !writer.has_avail() {
+            return Ok(0);
+        }
+
+        let mut buf = vec![0; writer.avail().min(BUFFER_CAPACITY)];
+        let timeout = self.ldisc.lock().read_timeout();
+        let read_len =
+            match self.wait_events(IoEvents::IN, timeout.as_ref(), || self.try_read(&mut buf)) {
+                // The read returns nothing if `VTIME` expires.
+                Err(err) if err.error() == Errno::ETIME => 0,
+                res => res?,
+            };
+
+        writer.write_fallible(&mut buf[..read_len].into())?;
         Ok(read_len)
     }
 
     fn write(&self, reader: &mut VmReader) -> Result<usize> {
-        let buf = reader.collect()?;
-        if let Ok(content) = alloc::str::from_utf8(&buf) {
-            print!("{content}");
-        } else {
-            println!("Not utf-8 content: {:?}", buf);
+        if self.driver.is_hung_up() {
+            return_errno_with_message!(Errno::EIO, "the tty is hung up");
+        }
+
+        let termios = self.ldisc.lock().termios();
+        if termios.c_lflags().contains(C_LFLAGS::TOSTOP) {
+            self.job_control.check_background_write()?;
         }
+
+        let buf = reader.collect()?;
+        process_output(&termios, &buf, &mut |chs| self.driver.push_output(chs));
         Ok(buf.len())
     }
 
@@ -101,22 +227,54 @@ impl FileIo for Tty {
         match cmd {
             IoctlCmd::TCGETS => {
                 // Get terminal attributes
-                let termios = self.ldisc.termios();
+                let termios = self.ldisc.lock().termios();
                 trace!("get termios = {:?}", termios);
                 current_userspace!().write_val(arg, &termios)?;
-                Ok(0)
+            }
+            IoctlCmd::TCSETS | IoctlCmd::TCSETSW | IoctlCmd::TCSETSF => {
+                // Set terminal attributes
+                self.job_control.check_background_write()?;
+                let termios = current_userspace!().read_val(arg)?;
+                debug!("set termios = {:?}", termios);
+
+                let mut ldisc = self.ldisc.lock();
+                let old_termios = ldisc.termios();
+                ldisc.set_termios(termios);
+                // The output is never buffered, so there is nothing to drain for `TCSETSW`.
+                let is_flushed = matches!(cmd, IoctlCmd::TCSETSF);
+                if is_flushed {
+                    ldisc.drain_input();
+                }
+                let is_readable = ldisc.is_readable();
+                drop(ldisc);
+                self.update_pollee(is_readable);
+
+                self.driver.on_termios_changed(&old_termios, &termios);
+                if is_flushed {
+                    self.driver.on_input_flushed();
+                }
             }
             IoctlCmd::TIOCGPGRP => {
+                if !self.is_controlling_terminal() {
+                    return_errno_with_message!(
+                        Errno::ENOTTY,
+                        "the tty is not controlling terminal"
+                    );
+                }
+
                 let Some(foreground) = self.foreground() else {
-                    return_errno_with_message!(Errno::ESRCH, "No fg process group")
+                    return_errno_with_message!(
+                        Errno::ESRCH,
+                        "the foreground process group does not exist"
+                    );
                 };
                 let fg_pgid = foreground.pgid();
                 debug!("fg_pgid = {}", fg_pgid);
                 current_userspace!().write_val(arg, &fg_pgid)?;
-                Ok(0)
             }
             IoctlCmd::TIOCSPGRP => {
                 // Set the process group id of fg progress group
+                self.job_control.check_background_write()?;
                 let pgid = {
                     let pgid: i32 = current_userspace!().read_val(arg)?;
                     if pgid < 0 {
@@ -126,50 +284,80 @@ impl FileIo for Tty {
                 };
 
                 self.set_foreground(&pgid)?;
-                Ok(0)
-            }
-            IoctlCmd::TCSETS => {
-                // Set terminal attributes
-                let termios = current_userspace!().read_val(arg)?;
-                debug!("set termios = {:?}", termios);
-                self.ldisc.set_termios(termios);
-                Ok(0)
-            }
-            IoctlCmd::TCSETSW => {
-                let termios = current_userspace!().read_val(arg)?;
-                debug!("set termios = {:?}", termios);
-                self.ldisc.set_termios(termios);
-                // TODO: drain output buffer
-                Ok(0)
             }
-            IoctlCmd::TCSETSF => {
-                let termios = current_userspace!().read_val(arg)?;
-                debug!("set termios = {:?}", termios);
-                self.ldisc.set_termios(termios);
-                self.ldisc.drain_input();
-                // TODO: drain output buffer
-                Ok(0)
+            IoctlCmd::TIOCGSID => {
+                if !self.is_controlling_terminal() {
+                    return_errno_with_message!(
+                        Errno::ENOTTY,
+                        "the tty is not controlling terminal"
+                    );
+                }
+
+                let sid = current!().session().unwrap().sid();
+                current_userspace!().write_val(arg, &sid)?;
             }
             IoctlCmd::TIOCGWINSZ => {
-                let winsize = self.ldisc.window_size();
+                let winsize = self.ldisc.lock().window_size();
                 current_userspace!().write_val(arg, &winsize)?;
-                Ok(0)
             }
             IoctlCmd::TIOCSWINSZ => {
                 let winsize = current_userspace!().read_val(arg)?;
-                self.ldisc.set_window_size(winsize);
-                Ok(0)
+                let old_winsize = {
+                    let mut ldisc = self.ldisc.lock();
+                    let old_winsize = ldisc.window_size();
+                    ldisc.set_window_size(winsize);
+                    old_winsize
+                };
+                if old_winsize != winsize {
+                    self.send_signal(SIGWINCH);
+                }
             }
             IoctlCmd::TIOCSCTTY => {
                 self.set_current_session()?;
-                Ok(0)
             }
-            _ => todo!(),
+            IoctlCmd::TIOCNOTTY => {
+                self.release_current_session()?;
+            }
+            IoctlCmd::FIONREAD => {
+                let buffer_len = self.ldisc.lock().buffer_len() as i32;
+                current_userspace!().write_val(arg, &buffer_len)?;
+            }
+            IoctlCmd::TIOCOUTQ => {
+                // The output is never buffered.
+                current_userspace!().write_val(arg, &0i32)?;
+            }
+            IoctlCmd::TCFLSH => {
+                self.job_control.check_background_write()?;
+                let (is_input, is_output) = match arg {
+                    TCIFLUSH => (true, false),
+                    TCOFLUSH => (false, true),
+                    TCIOFLUSH => (true, true),
+                    _ => return_errno_with_message!(Errno::EINVAL, "invalid queue selector"),
+                };
+                if is_input {
+                    self.ldisc.lock().drain_input();
+                    self.pollee.invalidate();
+                    self.driver.on_input_flushed();
+                }
+                if is_output {
+                    self.driver.flush_output();
+                }
+            }
+            IoctlCmd::TCSBRK => {
+                // The output is never buffered, so there is nothing to wait for.
+            }
+            _ => return_errno_with_message!(Errno::ENOTTY, "the ioctl is not supported by tty"),
         }
+
+        Ok(0)
     }
 }
 
-impl Terminal for Tty {
+const TCIFLUSH: usize = 0;
+const TCOFLUSH: usize = 1;
+const TCIOFLUSH: usize = 2;
+
+impl<D: TtyDriver> Terminal for Tty<D> {
     fn arc_self(&self) -> Arc<dyn Terminal> {
         self.weak_self.upgrade().unwrap() as _
     }
@@ -179,54 +367,17 @@ impl Terminal for Tty {
     }
 }
 
-impl Device for Tty {
+impl<D: TtyDriver> Device for Tty<D> {
     fn type_(&self) -> DeviceType {
         DeviceType::CharDevice
     }
 
     fn id(&self) -> DeviceId {
-        // The same value as /dev/console in linux.
-        DeviceId::new(88, 0)
+        DeviceId::new(D::DEVICE_MAJOR_ID, self.index)
     }
-}
-
-pub fn new_job_control_and_ldisc() -> (Arc<JobControl>, Arc<LineDiscipline>) {
-    let job_control = Arc::new(JobControl::new());
-
-    let send_signal = {
-        let cloned_job_control = job_control.clone();
-        move |signal: KernelSignal| {
-            let Some(foreground) = cloned_job_control.foreground() else {
-                return;
-            };
-
-            foreground.broadcast_signal(signal);
-        }
-    };
-
-    let ldisc = LineDiscipline::new(Arc::new(send_signal));
-
-    (job_control, ldisc)
-}
-
-pub fn get_n_tty() -> &'static Arc<Tty> {
-    N_TTY.get().unwrap()
-}
 
-/// Open `N_TTY` as the controlling terminal for the process. This method should
-/// only be called when creating the init process.
-pub fn open_ntty_as_controlling_terminal(process: &Process) -> Result<()> {
-    let tty = get_n_tty();
-
-    let session = &process.session().unwrap();
-    let process_group = process.process_group().unwrap();
-
-    session.set_terminal(|| {
-        tty.job_control.set_session(session);
-        Ok(tty.clone())
-    })?;
-
-    tty.job_control.set_foreground(Some(&process_group))?;
-
-    Ok(())
+    fn open(&self) -> Result<Option<Arc<dyn FileIo>>> {
+        let tty = self.weak_self.upgrade().unwrap();
+        D::open(tty).map(Some)
+    }
 }
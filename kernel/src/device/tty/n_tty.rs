@@ -0,0 +1,95 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use ostd::mm::{Infallible, VmReader};
+use spin::Once;
+
+use super::{driver::TtyDriver, Tty};
+use crate::{
+    prelude::*,
+    process::{Process, Terminal},
+};
+
+/// The driver of the system console.
+///
+/// The output is sent to all console devices (e.g., virtio-console), or to the serial port
+/// (i.e., the UART) if there are no console devices.
+pub struct ConsoleDriver {
+    _private: (),
+}
+
+impl TtyDriver for ConsoleDriver {
+    // The same value as `/dev/console` in Linux.
+    const DEVICE_MAJOR_ID: u32 = 5;
+
+    fn push_output(&self, chs: &[u8]) {
+        let devices = aster_console::all_devices_lock();
+        if devices.is_empty() {
+            for ch in chs {
+                ostd::arch::serial::send(*ch);
+            }
+            return;
+        }
+
+        for device in devices.values() {
+            device.send(chs);
+        }
+    }
+}
+
+/// The minor device ID of `/dev/console` in Linux.
+const CONSOLE_INDEX: u32 = 1;
+
+static N_TTY: Once<Arc<Tty<ConsoleDriver>>> = Once::new();
+
+pub(super) fn init() {
+    let tty = Tty::new(CONSOLE_INDEX, ConsoleDriver { _private: () });
+    N_TTY.call_once(|| tty);
+
+    let devices = aster_console::all_devices();
+    for (_, device) in devices.iter() {
+        device.register_callback(&console_input_callback);
+    }
+
+    // FIXME: Serial input is not supported on RISC-V yet.
+    #[cfg(target_arch = "x86_64")]
+    if devices.is_empty() {
+        ostd::arch::serial::register_console_input_callback(&serial_input_callback);
+    }
+}
+
+fn console_input_callback(mut reader: VmReader<Infallible>) {
+    let tty = get_n_tty();
+
+    let mut buf = [0u8; 64];
+    while reader.has_remain() {
+        let len = reader.read(&mut VmWriter::from(buf.as_mut_slice()));
+        tty.push_input(&buf[..len]);
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+fn serial_input_callback(ch: u8) {
+    get_n_tty().push_input(&[ch]);
+}
+
+pub fn get_n_tty() -> &'static Arc<Tty<ConsoleDriver>> {
+    N_TTY.get().unwrap()
+}
+
+/// Open `N_TTY` as the controlling terminal for the process. This method should
+/// only be called when creating the init process.
+pub fn open_ntty_as_controlling_terminal(process: &Process) -> Result<()> {
+    let tty = get_n_tty();
+
+    let session = &process.session().unwrap();
+    let process_group = process.process_group().unwrap();
+
+    session.set_terminal(|| {
+        tty.job_control().set_session(session);
+        Ok(tty.clone())
+    })?;
+
+    tty.job_control().set_foreground(Some(&process_group))?;
+
+    Ok(())
+}
@@ -286,6 +286,25 @@ impl KernelTermios {
     pub fn contains_iexten(&self) -> bool {
         self.c_lflags.contains(C_LFLAGS::IEXTEN)
     }
+
+    pub fn c_iflags(&self) -> C_IFLAGS {
+        self.c_iflags
+    }
+
+    pub fn c_oflags(&self) -> C_OFLAGS {
+        self.c_oflags
+    }
+
+    pub fn c_lflags(&self) -> C_LFLAGS {
+        self.c_lflags
+    }
+
+    /// Returns whether `ch` is the special character, which is disabled if it is zero (i.e.
+    /// `_POSIX_VDISABLE`).
+    pub fn is_special_char(&self, ch: u8, cc_c_char: CC_C_CHAR) -> bool {
+        let special_char = *self.get_special_char(cc_c_char);
+        special_char != 0 && ch == special_char
+    }
 }
 
 const fn control_character(c: char) -> u8 {
@@ -293,7 +312,7 @@ const fn control_character(c: char) -> u8 {
     c as u8 - b'A' + 1u8
 }
 
-#[derive(Debug, Clone, Copy, Default, Pod)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Pod)]
 #[repr(C)]
 pub struct WinSize {
     ws_row: u16,
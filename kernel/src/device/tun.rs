@@ -0,0 +1,471 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The TUN/TAP device file, i.e., `/dev/net/tun`.
+//!
+//! Each open file is attached to a TUN/TAP device with the `TUNSETIFF` ioctl, which creates
+//! the device if it does not exist. After that, the packets sent by the device can be read from
+//! the file, and the packets written to the file are received by the device. Unless the device
+//! is made persistent with `TUNSETPERSIST`, it is removed when the file is closed.
+//!
+//! Reference: <https://docs.kernel.org/networking/tuntap.html>.
+
+use core::mem::size_of;
+
+use crate::{
+    events::IoEvents,
+    fs::{
+        device::{Device, DeviceId, DeviceType},
+        inode_handle::FileIo,
+        utils::{IoctlCmd, StatusFlags},
+    },
+    net::iface::{TunIface, TunKind, IFNAMSIZ},
+    prelude::*,
+    process::{
+        credentials::capabilities::CapSet,
+        posix_thread::AsPosixThread,
+        signal::{PollHandle, Pollable},
+    },
+};
+
+/// Corresponds to `/dev/net/tun` in the file system.
+pub struct TunDevice;
+
+impl Device for TunDevice {
+    fn type_(&self) -> DeviceType {
+        DeviceType::MiscDevice
+    }
+
+    fn id(&self) -> DeviceId {
+        DeviceId::new(10, 200)
+    }
+
+    fn open(&self) -> Result<Option<Arc<dyn FileIo>>> {
+        Ok(Some(Arc::new(TunFile::new())))
+    }
+}
+
+impl Pollable for TunDevice {
+    fn poll(&self, mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        let events = IoEvents::IN | IoEvents::OUT;
+        events & mask
+    }
+}
+
+impl FileIo for TunDevice {
+    fn read(&self, _writer: &mut VmWriter) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "cannot read the TUN/TAP device");
+    }
+
+    fn write(&self, _reader: &mut VmReader) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "cannot write the TUN/TAP device");
+    }
+}
+
+bitflags! {
+    /// The flags of `TUNSETIFF` and `TUNGETIFF`.
+    struct TunFlags: u16 {
+        const TUN           = 0x0001;
+        const TAP           = 0x0002;
+        const NAPI          = 0x0010;
+        const NAPI_FRAGS    = 0x0020;
+        const MULTI_QUEUE   = 0x0100;
+        const PERSIST       = 0x0800;
+        /// Do not prepend the packet information (i.e., `struct tun_pi`).
+        const NO_PI         = 0x1000;
+        /// This flag is ignored, like Linux does.
+        const ONE_QUEUE     = 0x2000;
+        /// Prepend the virtio network header (i.e., `struct virtio_net_hdr`).
+        const VNET_HDR      = 0x4000;
+    }
+}
+
+/// The flags reported by `TUNGETFEATURES`.
+const TUN_FEATURES: TunFlags = TunFlags::TUN
+    .union(TunFlags::TAP)
+    .union(TunFlags::NO_PI)
+    .union(TunFlags::ONE_QUEUE)
+    .union(TunFlags::VNET_HDR);
+
+/// The offload flags of `TUNSETOFFLOAD`.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/if_tun.h>.
+const TUN_F_ALL: usize = 0x7f;
+
+/// The packet is truncated, which is reported in `struct tun_pi`.
+const TUN_PKT_STRIP: u16 = 0x0001;
+
+/// The default (and minimum) size of the virtio network header.
+const DEFAULT_VNET_HDR_SZ: usize = size_of::<CVirtioNetHdr>();
+
+/// `struct ifreq` in Linux, with only the flags in the union.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct CIfreq {
+    name: [u8; IFNAMSIZ],
+    flags: u16,
+    _pad: [u8; 22],
+}
+
+/// `struct tun_pi` in Linux.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct CTunPi {
+    #[expect(dead_code)]
+    flags: u16,
+    /// The protocol (i.e., EtherType) in the network byte order.
+    #[expect(dead_code)]
+    proto: u16,
+}
+
+/// `struct virtio_net_hdr` in Linux.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct CVirtioNetHdr {
+    flags: u8,
+    gso_type: u8,
+    #[expect(dead_code)]
+    hdr_len: u16,
+    #[expect(dead_code)]
+    gso_size: u16,
+    csum_start: u16,
+    csum_offset: u16,
+}
+
+/// The checksum is partial and should be completed with `csum_start` and `csum_offset`.
+const VIRTIO_NET_HDR_F_NEEDS_CSUM: u8 = 1;
+
+/// The packet does not need segmentation offload.
+const VIRTIO_NET_HDR_GSO_NONE: u8 = 0;
+
+const ETH_P_IP: u16 = 0x0800;
+const ETH_P_IPV6: u16 = 0x86dd;
+
+/// The length of Ethernet headers.
+const ETHERNET_HEADER_LEN: usize = 14;
+
+/// An open file of `/dev/net/tun`.
+struct TunFile {
+    state: Mutex<TunState>,
+}
+
+struct TunState {
+    tun: Option<Arc<TunIface>>,
+    flags: TunFlags,
+    vnet_hdr_sz: usize,
+}
+
+impl TunFile {
+    fn new() -> Self {
+        Self {
+            state: Mutex::new(TunState {
+                tun: None,
+                flags: TunFlags::empty(),
+                vnet_hdr_sz: DEFAULT_VNET_HDR_SZ,
+            }),
+        }
+    }
+
+    /// Returns the attached device with the flags and the size of the virtio network header.
+    fn attached(&self) -> Result<(Arc<TunIface>, TunFlags, usize)> {
+        let state = self.state.lock();
+        let Some(tun) = state.tun.as_ref() else {
+            return_errno_with_message!(Errno::EBADFD, "the file is not attached to a device");
+        };
+        if tun.is_removed() {
+            return_errno_with_message!(Errno::EBADFD, "the device has been removed");
+        }
+
+        Ok((tun.clone(), state.flags, state.vnet_hdr_sz))
+    }
+
+    fn set_iff(&self, ifreq: &mut CIfreq) -> Result<()> {
+        let current = current_thread!();
+        let credentials = current.as_posix_thread().unwrap().credentials();
+        if !credentials.effective_capset().contains(CapSet::NET_ADMIN) {
+            return_errno_with_message!(Errno::EPERM, "`CAP_NET_ADMIN` is required");
+        }
+
+        let flags = TunFlags::from_bits_truncate(ifreq.flags);
+        let kind = match flags & (TunFlags::TUN | TunFlags::TAP) {
+            TunFlags::TUN => TunKind::Tun,
+            TunFlags::TAP => TunKind::Tap,
+            _ => return_errno_with_message!(Errno::EINVAL, "the device kind is invalid"),
+        };
+        if flags.intersects(TunFlags::NAPI | TunFlags::NAPI_FRAGS | TunFlags::MULTI_QUEUE) {
+            return_errno_with_message!(Errno::EINVAL, "the flags are not supported");
+        }
+
+        let Ok(name) = CStr::from_bytes_until_nul(&ifreq.name) else {
+            return_errno_with_message!(Errno::EINVAL, "the device name is not terminated");
+        };
+        let Ok(name) = name.to_str() else {
+            return_errno_with_message!(Errno::EINVAL, "the device name is not valid UTF-8");
+        };
+        let name = match (name, kind) {
+            ("", TunKind::Tun) => "tun%d",
+            ("", TunKind::Tap) => "tap%d",
+            (name, _) => name,
+        };
+
+        let mut state = self.state.lock();
+        if state.tun.is_some() {
+            return_errno_with_message!(Errno::EINVAL, "the file is already attached to a device");
+        }
+
+        let tun = TunIface::attach(name, kind)?;
+        ifreq.name = name_to_c(tun.iface().name());
+        state.tun = Some(tun);
+        state.flags = flags & TUN_FEATURES;
+
+        Ok(())
+    }
+
+    fn get_iff(&self) -> Result<CIfreq> {
+        let (tun, flags, _) = self.attached()?;
+
+        let mut flags = flags;
+        if tun.is_persistent() {
+            flags |= TunFlags::PERSIST;
+        }
+
+        Ok(CIfreq {
+            name: name_to_c(tun.iface().name()),
+            flags: flags.bits(),
+            _pad: [0; 22],
+        })
+    }
+
+    fn try_read(
+        &self,
+        tun: &TunIface,
+        flags: TunFlags,
+        vnet_hdr_sz: usize,
+        writer: &mut VmWriter,
+    ) -> Result<usize> {
+        if tun.is_removed() {
+            return_errno_with_message!(Errno::EBADFD, "the device has been removed");
+        }
+
+        let mut header_len = 0;
+        if !flags.contains(TunFlags::NO_PI) {
+            header_len += size_of::<CTunPi>();
+        }
+        if flags.contains(TunFlags::VNET_HDR) {
+            header_len += vnet_hdr_sz;
+        }
+        if writer.avail() < header_len {
+            return_errno_with_message!(Errno::EINVAL, "the buffer is too small for the header");
+        }
+
+        let Some(packet) = tun.try_recv() else {
+            return_errno_with_message!(Errno::EAGAIN, "no packet is available");
+        };
+        let is_truncated = writer.avail() < header_len + packet.len();
+
+        let mut buf = Vec::with_capacity(header_len + packet.len());
+        if !flags.contains(TunFlags::NO_PI) {
+            let pi = CTunPi {
+                flags: if is_truncated { TUN_PKT_STRIP } else { 0 },
+                proto: packet_proto(tun.kind(), &packet).to_be(),
+            };
+            buf.extend_from_slice(pi.as_bytes());
+        }
+        if flags.contains(TunFlags::VNET_HDR) {
+            // The packets sent by the iface never have partial checksums or need segmentation
+            // offload, so the header is all zeros.
+            buf.resize(buf.len() + vnet_hdr_sz, 0);
+        }
+        buf.extend_from_slice(&packet);
+        buf.truncate(writer.avail());
+
+        writer.write_fallible(&mut VmReader::from(buf.as_slice()))?;
+
+        Ok(buf.len())
+    }
+}
+
+impl Drop for TunFile {
+    fn drop(&mut self) {
+        if let Some(tun) = self.state.get_mut().tun.take() {
+            tun.detach();
+        }
+    }
+}
+
+impl Pollable for TunFile {
+    fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
+        let Some(tun) = self.state.lock().tun.clone() else {
+            return IoEvents::ERR & (mask | IoEvents::ALWAYS_POLL);
+        };
+
+        tun.poll(mask, poller)
+    }
+}
+
+impl FileIo for TunFile {
+    fn read(&self, writer: &mut VmWriter) -> Result<usize> {
+        self.read_with_flags(writer, StatusFlags::empty())
+    }
+
+    fn write(&self, reader: &mut VmReader) -> Result<usize> {
+        self.write_with_flags(reader, StatusFlags::empty())
+    }
+
+    fn read_with_flags(&self, writer: &mut VmWriter, status_flags: StatusFlags) -> Result<usize> {
+        let (tun, flags, vnet_hdr_sz) = self.attached()?;
+
+        if status_flags.contains(StatusFlags::O_NONBLOCK) {
+            self.try_read(&tun, flags, vnet_hdr_sz, writer)
+        } else {
+            self.wait_events(IoEvents::IN, None, || {
+                self.try_read(&tun, flags, vnet_hdr_sz, writer)
+            })
+        }
+    }
+
+    fn write_with_flags(&self, reader: &mut VmReader, _status_flags: StatusFlags) -> Result<usize> {
+        let (tun, flags, vnet_hdr_sz) = self.attached()?;
+
+        let buf = reader.collect()?;
+        let mut rest = buf.as_slice();
+
+        if !flags.contains(TunFlags::NO_PI) {
+            let Some(packet) = rest.get(size_of::<CTunPi>()..) else {
+                return_errno_with_message!(Errno::EINVAL, "the packet information is too short");
+            };
+            rest = packet;
+        }
+
+        let mut vnet_hdr = None;
+        if flags.contains(TunFlags::VNET_HDR) {
+            let Some(packet) = rest.get(vnet_hdr_sz..) else {
+                return_errno_with_message!(Errno::EINVAL, "the virtio network header is too short");
+            };
+            vnet_hdr = Some(CVirtioNetHdr::from_bytes(&rest[..DEFAULT_VNET_HDR_SZ]));
+            rest = packet;
+        }
+
+        let mut packet = rest.to_vec();
+        if let Some(vnet_hdr) = vnet_hdr {
+            apply_vnet_hdr(&vnet_hdr, &mut packet)?;
+        }
+
+        match tun.kind() {
+            TunKind::Tun => {
+                if !matches!(packet.first().map(|byte| byte >> 4), Some(4 | 6)) {
+                    return_errno_with_message!(Errno::EINVAL, "the packet is not an IP packet");
+                }
+            }
+            TunKind::Tap => {
+                if packet.len() < ETHERNET_HEADER_LEN {
+                    return_errno_with_message!(Errno::EINVAL, "the frame is too short");
+                }
+            }
+        }
+
+        tun.send(packet);
+
+        Ok(buf.len())
+    }
+
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        let user_space = current_userspace!();
+
+        match cmd {
+            IoctlCmd::TUNSETIFF => {
+                let mut ifreq: CIfreq = user_space.read_val(arg)?;
+                self.set_iff(&mut ifreq)?;
+                user_space.write_val(arg, &ifreq)?;
+            }
+            IoctlCmd::TUNGETIFF => user_space.write_val(arg, &self.get_iff()?)?,
+            IoctlCmd::TUNSETPERSIST => {
+                let (tun, _, _) = self.attached()?;
+                tun.set_persistent(arg != 0);
+            }
+            IoctlCmd::TUNGETFEATURES => {
+                user_space.write_val(arg, &(TUN_FEATURES.bits() as u32))?;
+            }
+            IoctlCmd::TUNSETOFFLOAD => {
+                self.attached()?;
+                // The offloads only allow the user space to receive packets with partial
+                // checksums or large packets, which the iface never sends.
+                if arg & !TUN_F_ALL != 0 {
+                    return_errno_with_message!(Errno::EINVAL, "the offload flags are invalid");
+                }
+            }
+            IoctlCmd::TUNGETVNETHDRSZ => {
+                let vnet_hdr_sz = self.state.lock().vnet_hdr_sz as i32;
+                user_space.write_val(arg, &vnet_hdr_sz)?;
+            }
+            IoctlCmd::TUNSETVNETHDRSZ => {
+                let vnet_hdr_sz: i32 = user_space.read_val(arg)?;
+                if vnet_hdr_sz < DEFAULT_VNET_HDR_SZ as i32 {
+                    return_errno_with_message!(
+                        Errno::EINVAL,
+                        "the virtio network header size is too small"
+                    );
+                }
+                self.state.lock().vnet_hdr_sz = vnet_hdr_sz as usize;
+            }
+            _ => return_errno_with_message!(Errno::ENOTTY, "unsupported ioctl"),
+        }
+
+        Ok(0)
+    }
+}
+
+fn name_to_c(name: &str) -> [u8; IFNAMSIZ] {
+    let mut c_name = [0u8; IFNAMSIZ];
+    c_name[..name.len()].copy_from_slice(name.as_bytes());
+    c_name
+}
+
+/// Returns the protocol (i.e., EtherType) of the packet in the host byte order.
+fn packet_proto(kind: TunKind, packet: &[u8]) -> u16 {
+    match kind {
+        TunKind::Tun => match packet.first().map(|byte| byte >> 4) {
+            Some(6) => ETH_P_IPV6,
+            _ => ETH_P_IP,
+        },
+        TunKind::Tap => packet
+            .get(12..ETHERNET_HEADER_LEN)
+            .map_or(0, |proto| u16::from_be_bytes([proto[0], proto[1]])),
+    }
+}
+
+/// Handles the virtio network header of a packet that is written by the user space.
+///
+/// If the header says that the checksum is partial, the checksum is completed. Segmentation
+/// offload is not supported.
+fn apply_vnet_hdr(vnet_hdr: &CVirtioNetHdr, packet: &mut [u8]) -> Result<()> {
+    if vnet_hdr.gso_type != VIRTIO_NET_HDR_GSO_NONE {
+        return_errno_with_message!(Errno::EINVAL, "segmentation offload is not supported");
+    }
+    if vnet_hdr.flags & VIRTIO_NET_HDR_F_NEEDS_CSUM == 0 {
+        return Ok(());
+    }
+
+    let csum_start = vnet_hdr.csum_start as usize;
+    let csum_pos = csum_start + vnet_hdr.csum_offset as usize;
+    if csum_pos + 2 > packet.len() {
+        return_errno_with_message!(Errno::EINVAL, "the checksum offset is out of bounds");
+    }
+
+    // The checksum field contains the checksum of the pseudo header, so the checksum can be
+    // completed by summing up the bytes from `csum_start` to the end.
+    let mut sum = 0u32;
+    for chunk in packet[csum_start..].chunks(2) {
+        let word = match chunk {
+            [high, low] => u16::from_be_bytes([*high, *low]),
+            [high] => u16::from_be_bytes([*high, 0]),
+            _ => unreachable!(),
+        };
+        sum += word as u32;
+    }
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    packet[csum_pos..csum_pos + 2].copy_from_slice(&(!(sum as u16)).to_be_bytes());
+
+    Ok(())
+}
@@ -0,0 +1,230 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The watchdog devices, i.e., `/dev/watchdog` and `/dev/watchdogN`.
+//!
+//! The watchdogs are provided by `aster-watchdog`, and `/dev/watchdog` is the same as
+//! `/dev/watchdog0`. Like Linux, opening a device starts the watchdog, and writing any
+//! data to it keeps the watchdog alive. If "V" has been written before the device is
+//! closed, the watchdog is stopped; otherwise, it keeps running and will reset the system.
+//! The watchdogs are also controlled by the `WDIOC_*` ioctls.
+
+use alloc::format;
+
+use aster_watchdog::{Watchdog, WatchdogError, WatchdogInfo};
+
+use crate::{
+    events::IoEvents,
+    fs::{
+        device::{add_node, Device, DeviceId, DeviceType},
+        inode_handle::FileIo,
+        utils::IoctlCmd,
+    },
+    prelude::*,
+    process::signal::{PollHandle, Pollable},
+};
+
+/// The minor number of `/dev/watchdog`, which is a misc device.
+const WATCHDOG_MINOR: u32 = 130;
+
+/// The major number of `/dev/watchdogN`.
+///
+/// The number is allocated dynamically in Linux, so a fixed one is chosen here.
+pub(super) const WATCHDOG_MAJOR: u32 = 248;
+
+// The options of `WDIOC_SETOPTIONS`.
+const WDIOS_DISABLECARD: u32 = 0x0001;
+const WDIOS_ENABLECARD: u32 = 0x0002;
+
+pub(super) fn init() -> Result<()> {
+    for watchdog in aster_watchdog::all_watchdogs() {
+        let index = watchdog.index();
+        if index == 0 {
+            add_node(
+                Arc::new(WatchdogDevice::new(watchdog.clone(), true)),
+                "watchdog",
+            )?;
+        }
+        add_node(
+            Arc::new(WatchdogDevice::new(watchdog, false)),
+            &format!("watchdog{}", index),
+        )?;
+    }
+    Ok(())
+}
+
+/// Returns the watchdog device with the device number.
+pub(super) fn get_device(major: u32, minor: u32) -> Result<Arc<dyn Device>> {
+    let (index, is_misc) = match (major, minor) {
+        (10, WATCHDOG_MINOR) => (0, true),
+        (WATCHDOG_MAJOR, index) => (index as usize, false),
+        _ => return_errno_with_message!(Errno::EINVAL, "unsupported device"),
+    };
+    let Some(watchdog) = aster_watchdog::get_watchdog(index) else {
+        return_errno_with_message!(Errno::ENXIO, "the watchdog does not exist");
+    };
+    Ok(Arc::new(WatchdogDevice::new(watchdog, is_misc)))
+}
+
+/// `struct watchdog_info` in Linux.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct CWatchdogInfo {
+    options: u32,
+    firmware_version: u32,
+    identity: [u8; 32],
+}
+
+impl From<WatchdogInfo> for CWatchdogInfo {
+    fn from(info: WatchdogInfo) -> Self {
+        Self {
+            options: info.options.bits(),
+            firmware_version: info.firmware_version,
+            identity: info.identity,
+        }
+    }
+}
+
+impl From<WatchdogError> for Error {
+    fn from(err: WatchdogError) -> Self {
+        match err {
+            WatchdogError::InvalidArgs => Error::with_message(Errno::EINVAL, "invalid arguments"),
+            WatchdogError::NotSupported => {
+                Error::with_message(Errno::EOPNOTSUPP, "the operation is not supported")
+            }
+            WatchdogError::Busy => Error::with_message(Errno::EBUSY, "the watchdog is busy"),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct WatchdogDevice {
+    watchdog: Arc<Watchdog>,
+    is_misc: bool,
+}
+
+impl WatchdogDevice {
+    fn new(watchdog: Arc<Watchdog>, is_misc: bool) -> Self {
+        Self { watchdog, is_misc }
+    }
+}
+
+impl Device for WatchdogDevice {
+    fn type_(&self) -> DeviceType {
+        if self.is_misc {
+            DeviceType::MiscDevice
+        } else {
+            DeviceType::CharDevice
+        }
+    }
+
+    fn id(&self) -> DeviceId {
+        if self.is_misc {
+            DeviceId::new(10, WATCHDOG_MINOR)
+        } else {
+            DeviceId::new(WATCHDOG_MAJOR, self.watchdog.index() as u32)
+        }
+    }
+
+    fn open(&self) -> Result<Option<Arc<dyn FileIo>>> {
+        self.watchdog.open()?;
+        Ok(Some(Arc::new(WatchdogFile {
+            watchdog: self.watchdog.clone(),
+        })))
+    }
+}
+
+impl Pollable for WatchdogDevice {
+    fn poll(&self, mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        let events = IoEvents::OUT;
+        events & mask
+    }
+}
+
+impl FileIo for WatchdogDevice {
+    fn read(&self, _writer: &mut VmWriter) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the watchdog cannot be read");
+    }
+
+    fn write(&self, _reader: &mut VmReader) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the watchdog is not opened");
+    }
+}
+
+/// An opened watchdog.
+#[derive(Debug)]
+struct WatchdogFile {
+    watchdog: Arc<Watchdog>,
+}
+
+impl Drop for WatchdogFile {
+    fn drop(&mut self) {
+        self.watchdog.close();
+    }
+}
+
+impl Pollable for WatchdogFile {
+    fn poll(&self, mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        let events = IoEvents::OUT;
+        events & mask
+    }
+}
+
+impl FileIo for WatchdogFile {
+    fn read(&self, _writer: &mut VmWriter) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "the watchdog cannot be read");
+    }
+
+    fn write(&self, reader: &mut VmReader) -> Result<usize> {
+        let buf = reader.collect()?;
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        // The magic close is expected only if the last write contains "V".
+        self.watchdog.set_expects_close(buf.contains(&b'V'));
+        self.watchdog.keepalive();
+        Ok(buf.len())
+    }
+
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        let user_space = current_userspace!();
+        let watchdog = &self.watchdog;
+
+        match cmd {
+            IoctlCmd::WDIOC_GETSUPPORT => {
+                user_space.write_val(arg, &CWatchdogInfo::from(watchdog.info()))?
+            }
+            IoctlCmd::WDIOC_GETSTATUS => user_space.write_val(arg, &watchdog.status().bits())?,
+            IoctlCmd::WDIOC_GETBOOTSTATUS => {
+                user_space.write_val(arg, &watchdog.boot_status().bits())?
+            }
+            IoctlCmd::WDIOC_GETTEMP => {
+                return_errno_with_message!(Errno::EOPNOTSUPP, "the temperature is unknown")
+            }
+            IoctlCmd::WDIOC_SETOPTIONS => {
+                let options: u32 = user_space.read_val(arg)?;
+                if options & WDIOS_DISABLECARD != 0 {
+                    watchdog.stop()?;
+                }
+                if options & WDIOS_ENABLECARD != 0 {
+                    watchdog.start();
+                }
+            }
+            IoctlCmd::WDIOC_KEEPALIVE => watchdog.keepalive(),
+            IoctlCmd::WDIOC_SETTIMEOUT => {
+                let timeout: u32 = user_space.read_val(arg)?;
+                let timeout = watchdog.set_timeout(timeout)?;
+                user_space.write_val(arg, &timeout)?;
+            }
+            IoctlCmd::WDIOC_GETTIMEOUT => user_space.write_val(arg, &watchdog.timeout())?,
+            IoctlCmd::WDIOC_SETPRETIMEOUT => {
+                let pretimeout: u32 = user_space.read_val(arg)?;
+                watchdog.set_pretimeout(pretimeout)?;
+            }
+            IoctlCmd::WDIOC_GETPRETIMEOUT => user_space.write_val(arg, &watchdog.pretimeout())?,
+            IoctlCmd::WDIOC_GETTIMELEFT => user_space.write_val(arg, &watchdog.time_left())?,
+            _ => return_errno_with_message!(Errno::ENOTTY, "unsupported ioctl"),
+        }
+        Ok(0)
+    }
+}
@@ -0,0 +1,314 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The cgroup2 file system, which manages the hierarchy of cgroups (see
+//! [`crate::process::cgroup`]).
+//!
+//! It is usually mounted at "/sys/fs/cgroup". Each directory stands for a cgroup, which is
+//! created by `mkdir` and removed by `rmdir`, and contains the following files:
+//!  - `cgroup.procs`: the PIDs of the processes in the cgroup, where a process is moved into
+//!    the cgroup by writing its PID;
+//!  - `cgroup.controllers`: the controllers that are available in the cgroup;
+//!  - `cgroup.subtree_control`: the controllers that are enabled for the children, which are
+//!    enabled by writing `+<controller>`, and disabled by writing `-<controller>`;
+//!  - `cpuset.cpus` and `cpuset.mems`: the CPUs and the memory nodes of the cpuset;
+//!  - `cpuset.cpus.effective` and `cpuset.mems.effective`: the ones that are in effect;
+//!  - `cpuset.cpus.partition`: `member`, `root` or `isolated`;
+//!  - `cpuset.cpus.isolated`: the CPUs of the isolated partitions (only in the root cgroup).
+//!
+//! The files of the `cpuset` controller are always present in the cgroups other than the root
+//! cgroup, but the settings take effect only if the controller is enabled by the parent.
+//!
+//! Reference: <https://docs.kernel.org/admin-guide/cgroup-v2.html>
+
+use alloc::format;
+
+use spin::Once;
+
+use crate::{
+    fs::{
+        procfs::{
+            template::{DirOps, FileOps, ProcDir, ProcDirBuilder, ProcFileBuilder},
+            ProcFS, PROC_ROOT_INO,
+        },
+        utils::{DirEntryVecExt, Inode, InodeMode, NAME_MAX},
+    },
+    prelude::*,
+    process::{
+        cgroup::{
+            cpuset::{
+                format_cpu_list, format_node_list, isolated_cpus, parse_cpu_list, parse_node_list,
+            },
+            Cgroup,
+        },
+        process_table, Pid,
+    },
+};
+
+const CGROUP2_SUPER_MAGIC: u64 = 0x63677270;
+
+/// Returns the instance of the cgroup2 file system.
+///
+/// There is only one hierarchy, so all the mounts of the file system share the same files.
+pub fn singleton() -> &'static Arc<ProcFS> {
+    static CGROUPFS: Once<Arc<ProcFS>> = Once::new();
+
+    CGROUPFS.call_once(|| ProcFS::new_with_root(CGROUP2_SUPER_MAGIC, CgroupDirOps::new_root_inode))
+}
+
+/// Represents the inode of the directory of a cgroup.
+struct CgroupDirOps(Arc<Cgroup>);
+
+impl CgroupDirOps {
+    fn new_root_inode(fs: Weak<ProcFS>) -> Arc<dyn Inode> {
+        let inode = ProcDirBuilder::new(Self(Cgroup::root().clone()))
+            .fs(fs)
+            .ino(PROC_ROOT_INO)
+            .build()
+            .unwrap();
+        inode
+            .set_mode(InodeMode::from_bits_truncate(0o755))
+            .unwrap();
+        inode
+    }
+
+    fn new_inode(cgroup: Arc<Cgroup>, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        let inode = ProcDirBuilder::new(Self(cgroup))
+            .parent(parent)
+            .build()
+            .unwrap();
+        inode
+            .set_mode(InodeMode::from_bits_truncate(0o755))
+            .unwrap();
+        inode
+    }
+
+    fn files(&self) -> &'static [CgroupFile] {
+        if self.0.is_root() {
+            CgroupFile::ROOT_FILES
+        } else {
+            CgroupFile::CHILD_FILES
+        }
+    }
+}
+
+impl DirOps for CgroupDirOps {
+    fn lookup_child(&self, this_ptr: Weak<dyn Inode>, name: &str) -> Result<Arc<dyn Inode>> {
+        if let Some(file) = self.files().iter().find(|file| file.name() == name) {
+            return Ok(CgroupFileOps::new_inode(self.0.clone(), *file, this_ptr));
+        }
+        if let Some(child) = self.0.child(name) {
+            return Ok(CgroupDirOps::new_inode(child, this_ptr));
+        }
+        return_errno!(Errno::ENOENT);
+    }
+
+    fn populate_children(&self, this_ptr: Weak<dyn Inode>) {
+        let this = {
+            let this = this_ptr.upgrade().unwrap();
+            this.downcast_ref::<ProcDir<CgroupDirOps>>().unwrap().this()
+        };
+        let mut cached_children = this.cached_children().write();
+        for file in self.files() {
+            cached_children.put_entry_if_not_found(file.name(), || {
+                CgroupFileOps::new_inode(self.0.clone(), *file, this_ptr.clone())
+            });
+        }
+        for child in self.0.children() {
+            cached_children.put_entry_if_not_found(child.name(), || {
+                CgroupDirOps::new_inode(child.clone(), this_ptr.clone())
+            });
+        }
+    }
+
+    fn create_dir(
+        &self,
+        this_ptr: Weak<dyn Inode>,
+        name: &str,
+        _mode: InodeMode,
+    ) -> Result<Arc<dyn Inode>> {
+        if name.len() > NAME_MAX {
+            return_errno_with_message!(Errno::ENAMETOOLONG, "the name is too long");
+        }
+        let child = self.0.create_child(name)?;
+        Ok(CgroupDirOps::new_inode(child, this_ptr))
+    }
+
+    fn remove_dir(&self, name: &str) -> Result<()> {
+        self.0.remove_child(name)
+    }
+}
+
+/// The files in the directory of a cgroup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CgroupFile {
+    Procs,
+    Controllers,
+    SubtreeControl,
+    CpusetCpus,
+    CpusetCpusEffective,
+    CpusetCpusPartition,
+    CpusetCpusIsolated,
+    CpusetMems,
+    CpusetMemsEffective,
+}
+
+impl CgroupFile {
+    const ROOT_FILES: &'static [Self] = &[
+        Self::Procs,
+        Self::Controllers,
+        Self::SubtreeControl,
+        Self::CpusetCpusEffective,
+        Self::CpusetCpusIsolated,
+        Self::CpusetMemsEffective,
+    ];
+
+    const CHILD_FILES: &'static [Self] = &[
+        Self::Procs,
+        Self::Controllers,
+        Self::SubtreeControl,
+        Self::CpusetCpus,
+        Self::CpusetCpusEffective,
+        Self::CpusetCpusPartition,
+        Self::CpusetMems,
+        Self::CpusetMemsEffective,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Procs => "cgroup.procs",
+            Self::Controllers => "cgroup.controllers",
+            Self::SubtreeControl => "cgroup.subtree_control",
+            Self::CpusetCpus => "cpuset.cpus",
+            Self::CpusetCpusEffective => "cpuset.cpus.effective",
+            Self::CpusetCpusPartition => "cpuset.cpus.partition",
+            Self::CpusetCpusIsolated => "cpuset.cpus.isolated",
+            Self::CpusetMems => "cpuset.mems",
+            Self::CpusetMemsEffective => "cpuset.mems.effective",
+        }
+    }
+
+    fn is_writable(&self) -> bool {
+        matches!(
+            self,
+            Self::Procs
+                | Self::SubtreeControl
+                | Self::CpusetCpus
+                | Self::CpusetCpusPartition
+                | Self::CpusetMems
+        )
+    }
+}
+
+/// Represents the inode of a file of a cgroup.
+struct CgroupFileOps {
+    cgroup: Arc<Cgroup>,
+    file: CgroupFile,
+}
+
+impl CgroupFileOps {
+    fn new_inode(cgroup: Arc<Cgroup>, file: CgroupFile, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        let inode = ProcFileBuilder::new(Self { cgroup, file })
+            .parent(parent)
+            .build()
+            .unwrap();
+        if file.is_writable() {
+            inode
+                .set_mode(InodeMode::from_bits_truncate(0o644))
+                .unwrap();
+        }
+        inode
+    }
+}
+
+impl FileOps for CgroupFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let cpuset = self.cgroup.cpuset();
+        let output = match self.file {
+            CgroupFile::Procs => {
+                let mut pids: Vec<Pid> = self
+                    .cgroup
+                    .processes()
+                    .iter()
+                    .map(|process| process.pid())
+                    .collect();
+                pids.sort_unstable();
+                pids.iter().map(|pid| format!("{}\n", pid)).collect()
+            }
+            CgroupFile::Controllers => format!("{}\n", self.cgroup.controllers().join(" ")),
+            CgroupFile::SubtreeControl => {
+                if self.cgroup.is_cpuset_enabled() {
+                    String::from("cpuset\n")
+                } else {
+                    String::from("\n")
+                }
+            }
+            CgroupFile::CpusetCpus => format!("{}\n", format_cpu_list(&cpuset.cpus())),
+            CgroupFile::CpusetCpusEffective => {
+                format!("{}\n", format_cpu_list(&cpuset.effective_cpus()))
+            }
+            CgroupFile::CpusetCpusPartition => match cpuset.partition() {
+                (partition, true) => format!("{}\n", partition.as_str()),
+                (partition, false) => format!("{} invalid\n", partition.as_str()),
+            },
+            CgroupFile::CpusetCpusIsolated => {
+                format!("{}\n", format_cpu_list(&isolated_cpus().load()))
+            }
+            CgroupFile::CpusetMems => format!("{}\n", format_node_list(cpuset.mems())),
+            CgroupFile::CpusetMemsEffective => {
+                format!("{}\n", format_node_list(cpuset.effective_mems()))
+            }
+        };
+        Ok(output.into_bytes())
+    }
+
+    fn write_at(&self, _offset: usize, reader: &mut VmReader) -> Result<usize> {
+        let (input, len) = read_str(reader)?;
+        let cpuset = self.cgroup.cpuset();
+        match self.file {
+            CgroupFile::Procs => {
+                let Ok(pid) = input.trim().parse::<Pid>() else {
+                    return_errno_with_message!(Errno::EINVAL, "the PID is invalid");
+                };
+                let process = match pid {
+                    // Writing zero moves the writing process.
+                    0 => current!(),
+                    pid => process_table::get_process(pid)
+                        .ok_or_else(|| Error::with_message(Errno::ESRCH, "no such process"))?,
+                };
+                self.cgroup.attach(&process)?;
+            }
+            CgroupFile::SubtreeControl => {
+                for token in input.split_whitespace() {
+                    let (is_enabled, controller) = if let Some(controller) = token.strip_prefix('+')
+                    {
+                        (true, controller)
+                    } else if let Some(controller) = token.strip_prefix('-') {
+                        (false, controller)
+                    } else {
+                        return_errno_with_message!(Errno::EINVAL, "invalid token");
+                    };
+                    if controller != "cpuset" {
+                        return_errno_with_message!(Errno::ENOENT, "unknown controller");
+                    }
+                    self.cgroup.set_cpuset_enabled(is_enabled)?;
+                }
+            }
+            CgroupFile::CpusetCpus => cpuset.set_cpus(parse_cpu_list(&input)?)?,
+            CgroupFile::CpusetCpusPartition => cpuset.set_partition(input.trim().parse()?)?,
+            CgroupFile::CpusetMems => cpuset.set_mems(parse_node_list(&input)?)?,
+            _ => return_errno_with_message!(Errno::EACCES, "the file is read-only"),
+        }
+        Ok(len)
+    }
+}
+
+/// Reads the written string, and returns it with the length of the input.
+fn read_str(reader: &mut VmReader) -> Result<(String, usize)> {
+    let len = reader.remain();
+    let mut buf = vec![0u8; len];
+    reader.read_fallible(&mut VmWriter::from(buf.as_mut_slice()))?;
+
+    let input = String::from_utf8(buf)
+        .map_err(|_| Error::with_message(Errno::EINVAL, "the input is not valid UTF-8"))?;
+    Ok((input, len))
+}
@@ -399,6 +399,14 @@ impl PageCacheBackend for ExfatFS {
     fn npages(&self) -> usize {
         self.fs_size() / PAGE_SIZE
     }
+
+    fn plug(&self) {
+        self.block_device.plug();
+    }
+
+    fn unplug(&self) {
+        self.block_device.unplug();
+    }
 }
 
 impl FileSystem for ExfatFS {
@@ -175,6 +175,14 @@ impl PageCacheBackend for ExfatInode {
     fn npages(&self) -> usize {
         self.inner.read().size.align_up(PAGE_SIZE) / PAGE_SIZE
     }
+
+    fn plug(&self) {
+        self.inner.read().fs().block_device().plug();
+    }
+
+    fn unplug(&self) {
+        self.inner.read().fs().block_device().unplug();
+    }
 }
 
 impl ExfatInodeInner {
@@ -1152,6 +1160,7 @@ impl Inode for ExfatInode {
             atime: inner.atime.as_duration().unwrap_or_default(),
             mtime: inner.mtime.as_duration().unwrap_or_default(),
             ctime: inner.ctime.as_duration().unwrap_or_default(),
+            btime: None,
             type_: inner.inode_type,
             mode: inner.make_mode(),
             nlinks,
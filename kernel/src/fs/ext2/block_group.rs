@@ -5,8 +5,9 @@ use ostd::{const_assert, mm::UntypedMem};
 
 use super::{
     block_ptr::Ext2Bid,
+    csum::{crc16, crc32c},
     fs::Ext2,
-    inode::{Inode, InodeDesc, RawInode},
+    inode::{update_raw_inode_checksum, Inode, InodeDesc, RawInode},
     prelude::*,
     super_block::SuperBlock,
 };
@@ -42,11 +43,26 @@ impl BlockGroup {
                 let descriptor = {
                     // Read the block group descriptor
                     // TODO: if the main is corrupted, should we load the backup?
-                    let offset = idx * core::mem::size_of::<RawGroupDescriptor>();
-                    let raw_descriptor = group_descriptors_segment
-                        .read_val::<RawGroupDescriptor>(offset)
-                        .unwrap();
-                    GroupDescriptor::from(raw_descriptor)
+                    let raw_descriptor = RawGroupDescriptor::load(
+                        group_descriptors_segment,
+                        idx,
+                        super_block.desc_size(),
+                    );
+                    if raw_descriptor.block_bitmap_hi != 0
+                        || raw_descriptor.inode_bitmap_hi != 0
+                        || raw_descriptor.inode_table_hi != 0
+                    {
+                        return_errno_with_message!(
+                            Errno::EINVAL,
+                            "block group metadata is beyond 2^32 blocks"
+                        );
+                    }
+                    let mut descriptor = GroupDescriptor::from(raw_descriptor);
+                    if !super_block.has_group_desc_csum() {
+                        // The flags are meaningful only if the descriptors have checksums.
+                        descriptor.flags = GroupFlags::empty();
+                    }
+                    descriptor
                 };
 
                 let get_bitmap = |bid: Ext2Bid, capacity: usize| -> Result<IdAlloc> {
@@ -58,19 +74,29 @@ impl BlockGroup {
                     Ok(IdAlloc::from_bytes_with_capacity(&buf, capacity))
                 };
 
-                let block_bitmap = get_bitmap(
-                    descriptor.block_bitmap_bid,
-                    super_block.blocks_per_group() as usize,
-                )?;
-                let inode_bitmap = get_bitmap(
-                    descriptor.inode_bitmap_bid,
-                    super_block.inodes_per_group() as usize,
-                )?;
+                let block_bitmap = if descriptor.flags.contains(GroupFlags::BLOCK_UNINIT) {
+                    uninit_block_bitmap(group_descriptors_segment, idx, super_block)
+                } else {
+                    get_bitmap(
+                        descriptor.block_bitmap_bid,
+                        super_block.blocks_per_group() as usize,
+                    )?
+                };
+                let inode_bitmap = if descriptor.flags.contains(GroupFlags::INODE_UNINIT) {
+                    IdAlloc::with_capacity(super_block.inodes_per_group() as usize)
+                } else {
+                    get_bitmap(
+                        descriptor.inode_bitmap_bid,
+                        super_block.inodes_per_group() as usize,
+                    )?
+                };
 
                 GroupMetadata {
                     descriptor,
                     block_bitmap,
                     inode_bitmap,
+                    inodes_per_group: super_block.inodes_per_group(),
+                    has_group_desc_csum: super_block.has_group_desc_csum(),
                 }
             };
 
@@ -137,7 +163,7 @@ impl BlockGroup {
         let inode_desc = Dirty::new(InodeDesc::try_from(raw_inode)?);
         let ino = inode_idx + self.idx as u32 * fs.inodes_per_group() + 1;
 
-        Ok(Inode::new(ino, self.idx, inode_desc, Arc::downgrade(&fs)))
+        Inode::new(ino, self.idx, inode_desc, Arc::downgrade(&fs))
     }
 
     /// Inserts the inode into the inode cache.
@@ -209,13 +235,36 @@ impl BlockGroup {
         inner.metadata.free_blocks(range);
     }
 
+    /// Initializes the raw inode metadata of a newly allocated inode.
+    ///
+    /// The whole on-disk inode is zeroed, since the inode tables of Ext4 may be
+    /// left uninitialized and the extra space may contain the stale fields.
+    pub fn init_raw_inode(&self, inode_idx: u32, extra_isize: u16) {
+        let inode_size = self.fs().inode_size();
+        let offset = (inode_idx as usize) * inode_size;
+        let pages = self.raw_inodes_cache.pages();
+        pages.write_bytes(offset, &vec![0u8; inode_size]).unwrap();
+        if inode_size > core::mem::size_of::<RawInode>() {
+            pages
+                .write_val(offset + core::mem::size_of::<RawInode>(), &extra_isize)
+                .unwrap();
+        }
+    }
+
     /// Writes back the raw inode metadata to the raw inode metadata cache.
     pub fn sync_raw_inode(&self, inode_idx: u32, raw_inode: &RawInode) {
-        let offset = (inode_idx as usize) * self.fs().inode_size();
-        self.raw_inodes_cache
-            .pages()
-            .write_val(offset, raw_inode)
-            .unwrap();
+        let fs = self.fs();
+        let offset = (inode_idx as usize) * fs.inode_size();
+        let pages = self.raw_inodes_cache.pages();
+        pages.write_val(offset, raw_inode).unwrap();
+
+        if let Some(csum_seed) = fs.csum_seed() {
+            let ino = inode_idx + self.idx as u32 * fs.inodes_per_group() + 1;
+            let mut buf = vec![0u8; fs.inode_size()];
+            pages.read_bytes(offset, &mut buf).unwrap();
+            update_raw_inode_checksum(csum_seed, ino, &mut buf);
+            pages.write_bytes(offset, &buf).unwrap();
+        }
     }
 
     /// Writes back the metadata of this group.
@@ -226,24 +275,44 @@ impl BlockGroup {
 
         let mut inner = self.bg_impl.inner.write();
         let fs = self.fs();
-        // Writes back the descriptor.
-        let raw_descriptor = RawGroupDescriptor::from(&inner.metadata.descriptor);
-        self.fs().sync_group_descriptor(self.idx, &raw_descriptor)?;
-
         let mut bio_waiter = BioWaiter::new();
         // Writes back the inode bitmap.
         let inode_bitmap_bid = Bid::new(inner.metadata.descriptor.inode_bitmap_bid as u64);
-        bio_waiter.concat(fs.block_device().write_bytes_async(
-            inode_bitmap_bid.to_offset(),
-            inner.metadata.inode_bitmap.as_bytes(),
-        )?);
+        let inode_bitmap =
+            bitmap_block(&inner.metadata.inode_bitmap, fs.inodes_per_group() as usize);
+        bio_waiter
+            .concat(fs.write_metadata_bytes_async(inode_bitmap_bid.to_offset(), &inode_bitmap)?);
 
         // Writes back the block bitmap.
         let block_bitmap_bid = Bid::new(inner.metadata.descriptor.block_bitmap_bid as u64);
-        bio_waiter.concat(fs.block_device().write_bytes_async(
-            block_bitmap_bid.to_offset(),
-            inner.metadata.block_bitmap.as_bytes(),
-        )?);
+        let block_bitmap =
+            bitmap_block(&inner.metadata.block_bitmap, fs.blocks_per_group() as usize);
+        bio_waiter
+            .concat(fs.write_metadata_bytes_async(block_bitmap_bid.to_offset(), &block_bitmap)?);
+
+        // Writes back the descriptor.
+        // Both bitmaps are initialized on the device now.
+        inner
+            .metadata
+            .descriptor
+            .flags
+            .remove(GroupFlags::INODE_UNINIT | GroupFlags::BLOCK_UNINIT);
+        let mut raw_descriptor = RawGroupDescriptor::from(&inner.metadata.descriptor);
+        if let Some(csum_seed) = fs.csum_seed() {
+            let inode_bitmap_csum = crc32c(
+                csum_seed,
+                &inode_bitmap[..fs.inodes_per_group() as usize / 8],
+            );
+            raw_descriptor.inode_bitmap_csum_lo = inode_bitmap_csum as u16;
+            raw_descriptor.inode_bitmap_csum_hi = (inode_bitmap_csum >> 16) as u16;
+            let block_bitmap_csum = crc32c(
+                csum_seed,
+                &block_bitmap[..fs.blocks_per_group() as usize / 8],
+            );
+            raw_descriptor.block_bitmap_csum_lo = block_bitmap_csum as u16;
+            raw_descriptor.block_bitmap_csum_hi = (block_bitmap_csum >> 16) as u16;
+        }
+        fs.sync_group_descriptor(self.idx, &raw_descriptor)?;
 
         // Waits for the completion of all submitted bios.
         bio_waiter.wait().ok_or_else(|| {
@@ -344,12 +413,24 @@ impl PageCacheBackend for BlockGroupImpl {
         self.fs
             .upgrade()
             .unwrap()
-            .write_blocks_async(bid, bio_segment)
+            .write_metadata_blocks_async(bid, bio_segment)
     }
 
     fn npages(&self) -> usize {
         self.raw_inodes_size.div_ceil(BLOCK_SIZE)
     }
+
+    fn plug(&self) {
+        if let Some(fs) = self.fs.upgrade() {
+            fs.block_device().plug();
+        }
+    }
+
+    fn unplug(&self) {
+        if let Some(fs) = self.fs.upgrade() {
+            fs.block_device().unplug();
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -363,6 +444,9 @@ struct GroupMetadata {
     descriptor: GroupDescriptor,
     block_bitmap: IdAlloc,
     inode_bitmap: IdAlloc,
+    inodes_per_group: u32,
+    /// Whether the descriptor keeps track of the unused inodes in the inode table.
+    has_group_desc_csum: bool,
 }
 
 impl GroupMetadata {
@@ -373,6 +457,13 @@ impl GroupMetadata {
     pub fn alloc_inode(&mut self, is_dir: bool) -> Option<u32> {
         let inode_idx = self.inode_bitmap.alloc()?;
         self.dec_free_inodes();
+        if self.has_group_desc_csum {
+            let used_inodes = self.inodes_per_group - self.descriptor.itable_unused as u32;
+            if inode_idx as u32 >= used_inodes {
+                self.descriptor.itable_unused =
+                    (self.inodes_per_group - inode_idx as u32 - 1) as u16;
+            }
+        }
         if is_dir {
             self.inc_dirs();
         }
@@ -472,6 +563,12 @@ struct GroupDescriptor {
     free_inodes_count: u16,
     /// Number of directories in group
     dirs_count: u16,
+    /// Flags of the group
+    flags: GroupFlags,
+    /// Number of unused inodes at the end of the inode table
+    itable_unused: u16,
+    /// The raw descriptor, whose uninterpreted fields are written back unchanged.
+    raw: RawGroupDescriptor,
 }
 
 impl From<RawGroupDescriptor> for GroupDescriptor {
@@ -483,15 +580,31 @@ impl From<RawGroupDescriptor> for GroupDescriptor {
             free_blocks_count: desc.free_blocks_count,
             free_inodes_count: desc.free_inodes_count,
             dirs_count: desc.dirs_count,
+            flags: GroupFlags::from_bits_truncate(desc.flags),
+            itable_unused: desc.itable_unused,
+            raw: desc,
         }
     }
 }
 
-const_assert!(core::mem::size_of::<RawGroupDescriptor>() == 32);
+bitflags! {
+    /// Flags of the block group.
+    struct GroupFlags: u16 {
+        /// The inode table and bitmap are not initialized
+        const INODE_UNINIT = 1 << 0;
+        /// The block bitmap is not initialized
+        const BLOCK_UNINIT = 1 << 1;
+        /// The inode table is zeroed
+        const ITABLE_ZEROED = 1 << 2;
+    }
+}
+
+const_assert!(core::mem::size_of::<RawGroupDescriptor>() == 64);
 
 /// The raw block group descriptor.
 ///
 /// The table starts on the first block following the superblock.
+/// Each descriptor is 32 bytes in length, unless the `IS_64BIT` feature is set.
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Pod)]
 pub(super) struct RawGroupDescriptor {
@@ -501,8 +614,40 @@ pub(super) struct RawGroupDescriptor {
     pub free_blocks_count: u16,
     pub free_inodes_count: u16,
     pub dirs_count: u16,
-    pad: u16,
-    reserved: [u32; 3],
+    pub flags: u16,
+    exclude_bitmap: u32,
+    pub block_bitmap_csum_lo: u16,
+    pub inode_bitmap_csum_lo: u16,
+    pub itable_unused: u16,
+    pub checksum: u16,
+    //
+    // These fields are valid for the 64-byte descriptors only.
+    //
+    pub block_bitmap_hi: u32,
+    pub inode_bitmap_hi: u32,
+    pub inode_table_hi: u32,
+    pub free_blocks_count_hi: u16,
+    pub free_inodes_count_hi: u16,
+    pub dirs_count_hi: u16,
+    pub itable_unused_hi: u16,
+    exclude_bitmap_hi: u32,
+    pub block_bitmap_csum_hi: u16,
+    pub inode_bitmap_csum_hi: u16,
+    reserved: u32,
+}
+
+impl RawGroupDescriptor {
+    /// Loads the descriptor of the block group pointed by `idx` from the descriptor table.
+    ///
+    /// The fields beyond `desc_size` are zeroed.
+    pub fn load(group_descriptors_segment: &USegment, idx: usize, desc_size: usize) -> Self {
+        let mut raw_descriptor = Self::new_zeroed();
+        let len = desc_size.min(core::mem::size_of::<Self>());
+        group_descriptors_segment
+            .read_bytes(idx * desc_size, &mut raw_descriptor.as_bytes_mut()[..len])
+            .unwrap();
+        raw_descriptor
+    }
 }
 
 impl From<&GroupDescriptor> for RawGroupDescriptor {
@@ -514,8 +659,102 @@ impl From<&GroupDescriptor> for RawGroupDescriptor {
             free_blocks_count: desc.free_blocks_count,
             free_inodes_count: desc.free_inodes_count,
             dirs_count: desc.dirs_count,
-            pad: 0u16,
-            reserved: [0u32; 3],
+            flags: desc.flags.bits(),
+            itable_unused: desc.itable_unused,
+            ..desc.raw
         }
     }
 }
+
+/// Computes the checksum of the raw descriptor `desc` of the block group pointed by `idx`.
+///
+/// The checksum is the low 16 bits of CRC32C if the `METADATA_CSUM` feature is set,
+/// or CRC16 if the `GDT_CSUM` feature is set.
+pub(super) fn group_desc_checksum(
+    uuid: &[u8; 16],
+    csum_seed: Option<u32>,
+    idx: usize,
+    desc: &[u8],
+) -> u16 {
+    let checksum_offset = core::mem::offset_of!(RawGroupDescriptor, checksum);
+    let group = (idx as u32).to_le_bytes();
+    let (before, after) = (
+        &desc[..checksum_offset],
+        &desc[checksum_offset + core::mem::size_of::<u16>()..],
+    );
+
+    if let Some(csum_seed) = csum_seed {
+        let mut csum = crc32c(csum_seed, &group);
+        csum = crc32c(csum, before);
+        csum = crc32c(csum, &[0u8; 2]);
+        csum = crc32c(csum, after);
+        return csum as u16;
+    }
+
+    let mut crc = crc16(!0, uuid);
+    crc = crc16(crc, &group);
+    crc = crc16(crc, before);
+    crc16(crc, after)
+}
+
+/// Returns a block containing the `bitmap` of `nbits` bits.
+///
+/// The bits past the end of the bitmap are set, as required by Ext4.
+fn bitmap_block(bitmap: &IdAlloc, nbits: usize) -> Vec<u8> {
+    let mut block = vec![0xffu8; BLOCK_SIZE];
+    let nbytes = nbits.div_ceil(8);
+    block[..nbytes].copy_from_slice(&bitmap.as_bytes()[..nbytes]);
+    if nbits % 8 != 0 {
+        block[nbits / 8] |= 0xff << (nbits % 8);
+    }
+    block
+}
+
+/// Constructs the block bitmap of a block group whose bitmap is not initialized.
+///
+/// The blocks in use are the backups of the superblock and the group descriptor table,
+/// the metadata of the block groups residing in this group, and the blocks past the
+/// end of the filesystem.
+fn uninit_block_bitmap(
+    group_descriptors_segment: &USegment,
+    idx: usize,
+    super_block: &SuperBlock,
+) -> IdAlloc {
+    let blocks_per_group = super_block.blocks_per_group() as usize;
+    let group_range = (idx * blocks_per_group)..((idx + 1) * blocks_per_group);
+    let mut bitmap = IdAlloc::with_capacity(blocks_per_group);
+    let mut mark_in_use = |bid: usize| {
+        if group_range.contains(&bid) {
+            let _ = bitmap.alloc_specific(bid - group_range.start);
+        }
+    };
+
+    for bid in (super_block.total_blocks() as usize)..group_range.end {
+        mark_in_use(bid);
+    }
+
+    if idx == 0 || super_block.is_backup_group(idx) {
+        let nblocks =
+            1 + super_block.group_descriptors_blocks() + super_block.reserved_gdt_blocks();
+        for bid in group_range.start..group_range.start + nblocks {
+            mark_in_use(bid);
+        }
+    }
+
+    let inode_table_blocks =
+        (super_block.inodes_per_group() as usize * super_block.inode_size()).div_ceil(BLOCK_SIZE);
+    let groups_per_flex = super_block.groups_per_flex();
+    let first_group = idx / groups_per_flex * groups_per_flex;
+    let last_group = (first_group + groups_per_flex).min(super_block.block_groups_count() as usize);
+    for group in first_group..last_group {
+        let desc =
+            RawGroupDescriptor::load(group_descriptors_segment, group, super_block.desc_size());
+        mark_in_use(desc.block_bitmap as usize);
+        mark_in_use(desc.inode_bitmap as usize);
+        for bid in desc.inode_table as usize..desc.inode_table as usize + inode_table_blocks {
+            mark_in_use(bid);
+        }
+    }
+
+    bitmap
+}
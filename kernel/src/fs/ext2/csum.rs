@@ -0,0 +1,93 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The checksum algorithms used by the on-disk structures of Ext4 and JBD2.
+//!
+//! Like the helpers of Linux, the functions here neither invert the initial value
+//! nor the result, so a checksum can be computed over several buffers in steps.
+
+/// The reflected polynomial of CRC32C (Castagnoli).
+const CRC32C_POLY: u32 = 0x82f6_3b78;
+
+/// The reflected polynomial of CRC16 (the one used by `crc16` of Linux).
+const CRC16_POLY: u16 = 0xa001;
+
+const CRC32C_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ CRC32C_POLY
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+const CRC16_TABLE: [u16; 256] = {
+    let mut table = [0u16; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u16;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ CRC16_POLY
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Updates the CRC32C checksum `crc` with `bytes`.
+pub(in crate::fs) fn crc32c(mut crc: u32, bytes: &[u8]) -> u32 {
+    for byte in bytes {
+        crc = (crc >> 8) ^ CRC32C_TABLE[((crc ^ *byte as u32) & 0xff) as usize];
+    }
+    crc
+}
+
+/// Updates the CRC16 checksum `crc` with `bytes`.
+pub(super) fn crc16(mut crc: u16, bytes: &[u8]) -> u16 {
+    for byte in bytes {
+        crc = (crc >> 8) ^ CRC16_TABLE[((crc ^ *byte as u16) & 0xff) as usize];
+    }
+    crc
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn crc32c_check_value() {
+        assert_eq!(!crc32c(!0, b"123456789"), 0xe306_9283);
+    }
+
+    #[ktest]
+    fn crc32c_in_steps() {
+        let whole = crc32c(!0, b"hello, world");
+        let steps = crc32c(crc32c(!0, b"hello, "), b"world");
+        assert_eq!(whole, steps);
+    }
+
+    #[ktest]
+    fn crc16_check_value() {
+        // CRC-16/ARC
+        assert_eq!(crc16(0, b"123456789"), 0xbb3d);
+    }
+}
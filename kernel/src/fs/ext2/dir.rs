@@ -2,7 +2,7 @@
 
 #![expect(dead_code)]
 
-use super::{inode::MAX_FNAME_LEN, prelude::*};
+use super::{csum::crc32c, inode::MAX_FNAME_LEN, prelude::*};
 
 /// The data structure in a directory's data block. It is stored in a linked list.
 ///
@@ -20,6 +20,7 @@ impl DirEntry {
     const ALIGN: usize = 4;
     const HEADER_LEN: usize = core::mem::size_of::<DirEntryHeader>();
     const PARENT_OFFSET: usize = Self::HEADER_LEN + Self::ALIGN;
+    const MIN_RECORD_LEN: usize = Self::HEADER_LEN + Self::ALIGN;
 
     /// Constructs a new `DirEntry` object with the specified inode (`ino`),
     /// name (`name`), and file type (`inode_type`).
@@ -93,6 +94,9 @@ pub(super) struct DirEntryHeader {
 }
 
 impl DirEntryHeader {
+    /// The type indicator of the directory tail, which holds the checksum of a block.
+    const TAIL_TYPE: u8 = 0xde;
+
     pub(super) fn new(ino: u32, inode_type: InodeType, name_len: usize) -> Self {
         debug_assert!(name_len <= MAX_FNAME_LEN);
 
@@ -104,6 +108,59 @@ impl DirEntryHeader {
             inode_type: DirEntryFileType::from(inode_type) as _,
         }
     }
+
+    fn tail() -> Self {
+        Self {
+            ino: 0,
+            record_len: DIR_TAIL_LEN as _,
+            name_len: 0,
+            inode_type: Self::TAIL_TYPE,
+        }
+    }
+
+    fn is_tail(&self) -> bool {
+        self.ino == 0
+            && self.record_len as usize == DIR_TAIL_LEN
+            && self.name_len == 0
+            && self.inode_type == Self::TAIL_TYPE
+    }
+}
+
+/// The length of the tail at the end of each directory block if the metadata
+/// checksums are enabled.
+///
+/// The tail is a fake entry with no inode, followed by the checksum of the block.
+pub(super) const DIR_TAIL_LEN: usize = 12;
+
+/// Writes an empty tail at the end of the directory block at `block_offset`.
+///
+/// The checksum in the tail is filled in when the block is written back.
+pub(super) fn write_dir_tail(page_cache: &PageCache, block_offset: usize) -> Result<()> {
+    debug_assert!(block_offset % BLOCK_SIZE == 0);
+    let tail_offset = block_offset + BLOCK_SIZE - DIR_TAIL_LEN;
+    page_cache
+        .pages()
+        .write_val(tail_offset, &DirEntryHeader::tail())?;
+    page_cache
+        .pages()
+        .write_val(tail_offset + DirEntry::HEADER_LEN, &0u32)?;
+    Ok(())
+}
+
+/// Sets the checksum in the tail of the directory block `block`.
+///
+/// The block is left untouched if it does not end with a tail.
+pub(super) fn set_dir_block_checksum(block: &mut [u8], csum_seed: u32) {
+    debug_assert_eq!(block.len(), BLOCK_SIZE);
+    let tail_offset = BLOCK_SIZE - DIR_TAIL_LEN;
+    let header =
+        DirEntryHeader::from_bytes(&block[tail_offset..tail_offset + DirEntry::HEADER_LEN]);
+    if !header.is_tail() {
+        return;
+    }
+
+    let csum = crc32c(csum_seed, &block[..tail_offset]);
+    block[BLOCK_SIZE - 4..].copy_from_slice(&csum.to_le_bytes());
 }
 
 /// The type indicator in the `DirEntry`.
@@ -158,9 +215,14 @@ pub struct DirEntryReader<'a> {
 
 /// An iterator for iterating `DirEntryItem` from the
 /// page cache given a start offset.
+///
+/// The unused entries, whose inode number is zero, are skipped
+/// unless `include_unused` is set.
 pub(super) struct DirEntryIter<'a> {
     page_cache: &'a PageCache,
     offset: usize,
+    end_offset: usize,
+    include_unused: bool,
 }
 
 impl<'a> DirEntryReader<'a> {
@@ -178,17 +240,30 @@ impl<'a> DirEntryReader<'a> {
         DirEntryIter {
             page_cache: self.page_cache,
             offset: self.from_offset,
+            end_offset: self.page_cache.pages().size(),
+            include_unused: false,
         }
     }
 
-    /// Returns an iterator for iterating `DirEntry`s.
-    pub fn iter_entries(&'a mut self) -> impl Iterator<Item = DirEntry> + 'a {
+    /// Returns an iterator for iterating `DirEntryItem`s, including the unused ones.
+    fn iter_all(&self) -> DirEntryIter<'a> {
+        DirEntryIter {
+            include_unused: true,
+            ..self.iter()
+        }
+    }
+
+    /// Returns an iterator for iterating `DirEntry`s along with their offsets.
+    pub fn iter_entries(&'a mut self) -> impl Iterator<Item = (usize, DirEntry)> + 'a {
         let iter = self.iter();
         iter.filter_map(|entry_item| match self.read_name(&entry_item) {
-            Ok(name_buf) => Some(DirEntry {
-                header: entry_item.header,
-                name: CStr256::from(name_buf),
-            }),
+            Ok(name_buf) => Some((
+                entry_item.offset,
+                DirEntry {
+                    header: entry_item.header,
+                    name: CStr256::from(name_buf),
+                },
+            )),
             Err(_) => None,
         })
     }
@@ -224,11 +299,53 @@ impl<'a> DirEntryReader<'a> {
         })
     }
 
+    /// Returns the target entry with the given name in the given blocks.
+    pub fn find_entry_item_in_blocks(
+        &mut self,
+        name: &str,
+        blocks: &[usize],
+    ) -> Option<DirEntryItem> {
+        let name_len = name.len();
+        let name_bytes = name.as_bytes();
+        for block in blocks {
+            let block_offset = block * BLOCK_SIZE;
+            let mut iter = DirEntryIter {
+                offset: block_offset,
+                end_offset: (block_offset + BLOCK_SIZE).min(self.page_cache.pages().size()),
+                ..self.iter()
+            };
+            let found = iter.find(|entry_item| {
+                if entry_item.name_len() != name_len {
+                    return false;
+                }
+
+                match self.read_name(entry_item) {
+                    Ok(name_buf) => name_buf == name_bytes,
+                    Err(_) => false,
+                }
+            });
+            if found.is_some() {
+                return found;
+            }
+        }
+        None
+    }
+
     /// Returns the number of entries in the directory.
     pub fn entry_count(&self) -> usize {
         self.iter().count()
     }
 
+    /// Whether the block at `block_offset` contains no entries.
+    fn is_block_empty(&self, block_offset: usize) -> bool {
+        let mut iter = DirEntryIter {
+            offset: block_offset,
+            end_offset: block_offset + BLOCK_SIZE,
+            ..self.iter()
+        };
+        iter.next().is_none()
+    }
+
     /// Reads the name of the entry from the page cache to the inner buffer.
     fn read_name(&mut self, entry_item: &DirEntryItem) -> Result<&[u8]> {
         if self.name_buf.is_none() {
@@ -247,12 +364,15 @@ impl<'a> DirEntryReader<'a> {
 impl DirEntryIter<'_> {
     /// Reads a `DirEntryItem` at the current offset.
     fn read_entry_item(&mut self) -> Result<DirEntryItem> {
-        if self.offset >= self.page_cache.pages().size() {
+        if self.offset >= self.end_offset {
             return_errno!(Errno::ENOENT);
         }
 
         let header = self.read_header()?;
         let record_len = header.record_len as usize;
+        if record_len < DirEntry::MIN_RECORD_LEN || record_len % DirEntry::ALIGN != 0 {
+            return_errno_with_message!(Errno::EUCLEAN, "invalid directory entry length");
+        }
         let item = DirEntryItem {
             header,
             offset: self.offset,
@@ -268,9 +388,6 @@ impl DirEntryIter<'_> {
             .page_cache
             .pages()
             .read_val::<DirEntryHeader>(self.offset)?;
-        if header.ino == 0 {
-            return_errno!(Errno::ENOENT);
-        }
         Ok(header)
     }
 }
@@ -279,7 +396,12 @@ impl Iterator for DirEntryIter<'_> {
     type Item = DirEntryItem;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.read_entry_item().ok()
+        loop {
+            let item = self.read_entry_item().ok()?;
+            if self.include_unused || !item.is_unused() {
+                return Some(item);
+            }
+        }
     }
 }
 
@@ -308,6 +430,11 @@ impl DirEntryItem {
         self.header.ino as _
     }
 
+    /// Whether the entry is unused, i.e., it has no inode.
+    pub fn is_unused(&self) -> bool {
+        self.header.ino == 0
+    }
+
     /// Modifies the inode number.
     pub fn set_ino(&mut self, ino: u32) {
         self.header.ino = ino as _;
@@ -340,8 +467,16 @@ impl DirEntryItem {
     }
 
     /// Returns the length of the gap between the current entry and the next entry.
+    ///
+    /// An unused entry is a gap as a whole, except for the directory tail.
     pub fn gap_len(&self) -> usize {
-        self.record_len() - self.actual_len()
+        if self.header.is_tail() {
+            0
+        } else if self.is_unused() {
+            self.record_len()
+        } else {
+            self.record_len() - self.actual_len()
+        }
     }
 }
 
@@ -349,15 +484,19 @@ impl DirEntryItem {
 pub struct DirEntryWriter<'a> {
     page_cache: &'a PageCache,
     offset: usize,
+    /// The length of the tail at the end of each new block, either zero or `DIR_TAIL_LEN`.
+    tail_len: usize,
     name_buf: Option<[u8; MAX_FNAME_LEN]>,
 }
 
 impl<'a> DirEntryWriter<'a> {
-    /// Constructs a writer with the given page cache and offset.
-    pub(super) fn new(page_cache: &'a PageCache, from_offset: usize) -> Self {
+    /// Constructs a writer with the given page cache, offset and length of the block tails.
+    pub(super) fn new(page_cache: &'a PageCache, from_offset: usize, tail_len: usize) -> Self {
+        debug_assert!(tail_len == 0 || tail_len == DIR_TAIL_LEN);
         Self {
             page_cache,
             offset: from_offset,
+            tail_len,
             name_buf: None,
         }
     }
@@ -391,8 +530,12 @@ impl<'a> DirEntryWriter<'a> {
         debug_assert_eq!(self.offset, DirEntry::PARENT_OFFSET);
 
         let mut parent_header = DirEntryHeader::new(parent_ino, InodeType::Dir, 2);
-        parent_header.record_len = (BLOCK_SIZE - self.offset) as _;
-        self.write_entry(&parent_header, "..")
+        parent_header.record_len = (BLOCK_SIZE - self.offset - self.tail_len) as _;
+        self.write_entry(&parent_header, "..")?;
+        if self.tail_len > 0 {
+            write_dir_tail(self.page_cache, 0)?;
+        }
+        Ok(())
     }
 
     /// Appends a new `DirEntry` starting from the current offset.
@@ -410,7 +553,7 @@ impl<'a> DirEntryWriter<'a> {
         debug_assert_eq!(header.name_len as usize, name_len);
         let name_bytes = name.as_bytes();
         let mut entry_item_with_enough_gap = None;
-        for entry_item in DirEntryReader::new(self.page_cache, self.offset).iter() {
+        for entry_item in DirEntryReader::new(self.page_cache, self.offset).iter_all() {
             if entry_item_with_enough_gap.is_none()
                 && entry_item.gap_len() >= header.record_len as usize
             {
@@ -421,6 +564,7 @@ impl<'a> DirEntryWriter<'a> {
             }
 
             if check_existence
+                && !entry_item.is_unused()
                 && entry_item.name_len() == name_len
                 && self.read_name(&entry_item)? == name_bytes
             {
@@ -442,6 +586,13 @@ impl<'a> DirEntryWriter<'a> {
         mut header: DirEntryHeader,
         name: &str,
     ) -> Result<()> {
+        // Reuse the unused entry as a whole.
+        if entry_with_enough_gap.is_unused() {
+            header.record_len = entry_with_enough_gap.record_len() as u16;
+            self.offset = entry_with_enough_gap.offset;
+            return self.write_entry(&header, name);
+        }
+
         // Write in the gap between existing entries.
         header.record_len = entry_with_enough_gap.gap_len() as u16;
         entry_with_enough_gap.set_record_len(entry_with_enough_gap.actual_len());
@@ -456,49 +607,67 @@ impl<'a> DirEntryWriter<'a> {
         let old_size = self.page_cache.pages().size();
         let new_size = old_size + BLOCK_SIZE;
         self.page_cache.resize(new_size)?;
-        header.record_len = BLOCK_SIZE as _;
+        header.record_len = (BLOCK_SIZE - self.tail_len) as _;
 
         self.offset = old_size;
-        self.write_entry(&header, name)
+        self.write_entry(&header, name)?;
+        if self.tail_len > 0 {
+            write_dir_tail(self.page_cache, old_size)?;
+        }
+        Ok(())
     }
 
     /// Removes and returns an existing `DirEntry` indicated by `name`.
+    ///
+    /// The space of the entry is merged into the previous entry in the same block.
+    /// If the entry is the first one in its block, it is marked as unused instead.
+    /// The trailing blocks that become empty are truncated.
     pub fn remove_entry(&mut self, name: &str) -> Result<DirEntryItem> {
         let mut pre_entry_item = None;
         let name_len = name.len();
         let name_bytes = name.as_bytes();
-        let mut iter = DirEntryReader::new(self.page_cache, DirEntry::PARENT_OFFSET).iter();
-        let Some(target_entry_item) = iter.find(|entry| {
+        let mut iter = DirEntryReader::new(self.page_cache, DirEntry::PARENT_OFFSET).iter_all();
+        let Some(mut target_entry_item) = iter.find(|entry| {
             if entry.offset < self.offset {
                 pre_entry_item = Some(*entry);
             }
 
             entry.offset == self.offset
+                && !entry.is_unused()
                 && entry.name_len() == name_len
                 && self.read_name(entry).unwrap() == name_bytes
         }) else {
             return_errno!(Errno::ENOENT);
         };
-        let is_last_entry = iter.next().is_none();
+        let removed_entry_item = target_entry_item;
+
+        let block_offset = target_entry_item.offset.align_down(BLOCK_SIZE);
+        match pre_entry_item {
+            Some(mut pre_entry_item) if pre_entry_item.offset >= block_offset => {
+                // Update the previous entry.
+                pre_entry_item
+                    .set_record_len(pre_entry_item.record_len() + target_entry_item.record_len());
+                self.offset = pre_entry_item.offset;
+                self.write_header_only(&pre_entry_item.header)?;
+            }
+            _ => {
+                target_entry_item.set_ino(0);
+                self.offset = target_entry_item.offset;
+                self.write_header_only(&target_entry_item.header)?;
+            }
+        }
 
-        let mut pre_entry_item = pre_entry_item.unwrap();
-        let pre_offset = pre_entry_item.offset;
-        if is_last_entry {
-            // Shrink the size.
-            let new_size = pre_offset.align_up(BLOCK_SIZE);
+        // Shrink the size. The first block is never freed.
+        let reader = DirEntryReader::new(self.page_cache, 0);
+        let mut new_size = self.page_cache.pages().size();
+        while new_size > BLOCK_SIZE && reader.is_block_empty(new_size - BLOCK_SIZE) {
+            new_size -= BLOCK_SIZE;
+        }
+        if new_size < self.page_cache.pages().size() {
             self.page_cache.resize(new_size)?;
-            pre_entry_item.set_record_len(new_size - pre_offset);
-            self.offset = pre_offset;
-            self.write_header_only(&pre_entry_item.header)?;
-        } else {
-            // Update the previous entry.
-            pre_entry_item
-                .set_record_len(pre_entry_item.record_len() + target_entry_item.record_len());
-            self.offset = pre_offset;
-            self.write_header_only(&pre_entry_item.header)?;
         }
 
-        Ok(target_entry_item)
+        Ok(removed_entry_item)
     }
 
     /// Renames the `DirEntry` from `old_name` to the `new_name` from the current offset.
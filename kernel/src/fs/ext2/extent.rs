@@ -0,0 +1,637 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Extent trees, which map the blocks of files in Ext4.
+//!
+//! The root of an extent tree is stored in the block pointers of the inode.
+//! Each node starts with a header, which is followed by index entries in the
+//! internal nodes, or by extents in the leaves.
+//!
+//! The tree is kept in memory as a sorted list of extents. When the inode is
+//! synced, a tree of the minimal depth is rebuilt from the list, reusing the
+//! device blocks of the old nodes.
+
+use super::{
+    block_ptr::{BlockPtrs, Ext2Bid},
+    csum::crc32c,
+    prelude::*,
+};
+
+const EXTENT_MAGIC: u16 = 0xf30a;
+const HEADER_LEN: usize = core::mem::size_of::<RawExtentHeader>();
+const ENTRY_LEN: usize = core::mem::size_of::<RawExtent>();
+const TAIL_LEN: usize = core::mem::size_of::<u32>();
+/// The maximum number of entries in the root, which lives in the block pointers.
+const ROOT_MAX_ENTRIES: usize = (core::mem::size_of::<BlockPtrs>() - HEADER_LEN) / ENTRY_LEN;
+/// The maximum number of entries in a node, leaving room for the checksum.
+const NODE_MAX_ENTRIES: usize = (BLOCK_SIZE - HEADER_LEN - TAIL_LEN) / ENTRY_LEN;
+const MAX_DEPTH: u16 = 5;
+/// The maximum length of an initialized extent.
+const MAX_INIT_LEN: Ext2Bid = 32768;
+/// The maximum length of an unwritten extent.
+const MAX_UNWRITTEN_LEN: Ext2Bid = 32767;
+
+/// An extent, which maps consecutive file blocks to consecutive device blocks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Extent {
+    /// The first file block.
+    block: Ext2Bid,
+    /// The number of blocks.
+    len: Ext2Bid,
+    /// The first device block.
+    start: Ext2Bid,
+    /// Whether the blocks are allocated but not yet written, which read as zeros.
+    is_unwritten: bool,
+}
+
+impl Extent {
+    fn end(&self) -> Ext2Bid {
+        self.block + self.len
+    }
+
+    fn device_range(&self) -> Range<Ext2Bid> {
+        self.start..self.start + self.len
+    }
+
+    /// Checks if `next` can be merged into this extent.
+    fn is_mergeable(&self, next: &Extent) -> bool {
+        let max_len = if self.is_unwritten {
+            MAX_UNWRITTEN_LEN
+        } else {
+            MAX_INIT_LEN
+        };
+        self.is_unwritten == next.is_unwritten
+            && self.end() == next.block
+            && self.start + self.len == next.start
+            && self.len + next.len <= max_len
+    }
+}
+
+/// How a range of file blocks is mapped.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(super) enum BlockMapping {
+    /// The blocks are mapped to the device blocks.
+    Mapped(Range<Ext2Bid>),
+    /// The given number of blocks are not mapped or not yet written,
+    /// which read as zeros.
+    Zeroed(Ext2Bid),
+}
+
+/// The in-memory extent tree of an inode.
+#[derive(Debug)]
+pub(super) struct ExtentTree {
+    /// The extents sorted by the file blocks.
+    extents: Vec<Extent>,
+    /// The device blocks of the nodes except the root.
+    node_bids: Vec<Ext2Bid>,
+    /// The checksum seed of the inode, if the metadata checksums are enabled.
+    csum_seed: Option<u32>,
+    is_dirty: bool,
+}
+
+impl ExtentTree {
+    /// Creates an empty extent tree.
+    pub fn new(csum_seed: Option<u32>) -> Self {
+        Self {
+            extents: Vec::new(),
+            node_bids: Vec::new(),
+            csum_seed,
+            is_dirty: false,
+        }
+    }
+
+    /// Returns the block pointers containing the root of an empty extent tree.
+    pub fn empty_root() -> BlockPtrs {
+        let mut block_ptrs = BlockPtrs::default();
+        let header = RawExtentHeader::new(0, ROOT_MAX_ENTRIES, 0);
+        block_ptrs.as_bytes_mut()[..HEADER_LEN].copy_from_slice(header.as_bytes());
+        block_ptrs
+    }
+
+    /// Loads the extent tree whose root is stored in `block_ptrs`.
+    ///
+    /// The other nodes are read by `read_node`.
+    pub fn load(
+        block_ptrs: &BlockPtrs,
+        csum_seed: Option<u32>,
+        read_node: &mut dyn FnMut(Ext2Bid, &mut [u8]) -> Result<()>,
+    ) -> Result<Self> {
+        let mut tree = Self::new(csum_seed);
+        tree.load_node(block_ptrs.as_bytes(), None, read_node)?;
+        Ok(tree)
+    }
+
+    fn load_node(
+        &mut self,
+        node: &[u8],
+        expected_depth: Option<u16>,
+        read_node: &mut dyn FnMut(Ext2Bid, &mut [u8]) -> Result<()>,
+    ) -> Result<()> {
+        let header = RawExtentHeader::from_bytes(&node[..HEADER_LEN]);
+        let max_entries = (node.len() - HEADER_LEN) / ENTRY_LEN;
+        if header.magic != EXTENT_MAGIC
+            || header.entries > header.max
+            || header.max as usize > max_entries
+            || header.depth > MAX_DEPTH
+            || expected_depth.is_some_and(|depth| depth != header.depth)
+        {
+            return_errno_with_message!(Errno::EUCLEAN, "corrupted extent tree node");
+        }
+
+        let entries = (0..header.entries as usize)
+            .map(|idx| &node[HEADER_LEN + idx * ENTRY_LEN..HEADER_LEN + (idx + 1) * ENTRY_LEN]);
+        if header.depth == 0 {
+            for entry in entries {
+                let raw_extent = RawExtent::from_bytes(entry);
+                let extent = raw_extent.to_extent()?;
+                if self
+                    .extents
+                    .last()
+                    .is_some_and(|last| last.end() > extent.block)
+                {
+                    return_errno_with_message!(Errno::EUCLEAN, "overlapping extents");
+                }
+                self.extents.push(extent);
+            }
+            return Ok(());
+        }
+
+        let mut child = vec![0u8; BLOCK_SIZE];
+        for entry in entries {
+            let raw_index = RawExtentIndex::from_bytes(entry);
+            if raw_index.leaf_hi != 0 {
+                return_errno_with_message!(Errno::EINVAL, "extent tree node beyond 2^32 blocks");
+            }
+            read_node(raw_index.leaf_lo, &mut child)?;
+            self.node_bids.push(raw_index.leaf_lo);
+            self.load_node(&child, Some(header.depth - 1), read_node)?;
+        }
+        Ok(())
+    }
+
+    /// Returns whether the tree is modified since it is loaded or persisted.
+    pub fn is_dirty(&self) -> bool {
+        self.is_dirty
+    }
+
+    /// Returns the number of device blocks used by the file, including the nodes.
+    pub fn allocated_blocks(&self) -> u64 {
+        let data_blocks: u64 = self.extents.iter().map(|extent| extent.len as u64).sum();
+        data_blocks + self.node_bids.len() as u64
+    }
+
+    /// Returns the device block next to the last mapped one,
+    /// which is a good place for the blocks allocated next.
+    pub fn goal(&self) -> Option<Ext2Bid> {
+        self.extents.last().map(|extent| extent.start + extent.len)
+    }
+
+    /// Returns the mappings of the file blocks in `range`.
+    pub fn lookup(&self, range: Range<Ext2Bid>) -> Vec<BlockMapping> {
+        let mut mappings = Vec::new();
+        let mut current = range.start;
+        let mut idx = self
+            .extents
+            .partition_point(|extent| extent.end() <= current);
+        while current < range.end {
+            match self.extents.get(idx) {
+                Some(extent) if extent.block <= current => {
+                    let end = extent.end().min(range.end);
+                    if extent.is_unwritten {
+                        mappings.push(BlockMapping::Zeroed(end - current));
+                    } else {
+                        let start = extent.start + (current - extent.block);
+                        mappings.push(BlockMapping::Mapped(start..start + (end - current)));
+                    }
+                    current = end;
+                    idx += 1;
+                }
+                Some(extent) => {
+                    let end = extent.block.min(range.end);
+                    mappings.push(BlockMapping::Zeroed(end - current));
+                    current = end;
+                }
+                None => {
+                    mappings.push(BlockMapping::Zeroed(range.end - current));
+                    current = range.end;
+                }
+            }
+        }
+        mappings
+    }
+
+    /// Returns the file blocks in `range` that are not mapped.
+    ///
+    /// The unwritten blocks are considered as mapped.
+    pub fn holes(&self, range: Range<Ext2Bid>) -> Vec<Range<Ext2Bid>> {
+        let mut holes = Vec::new();
+        let mut current = range.start;
+        let idx = self
+            .extents
+            .partition_point(|extent| extent.end() <= current);
+        for extent in &self.extents[idx..] {
+            if current >= range.end {
+                break;
+            }
+            if extent.block > current {
+                holes.push(current..extent.block.min(range.end));
+            }
+            current = current.max(extent.end());
+        }
+        if current < range.end {
+            holes.push(current..range.end);
+        }
+        holes
+    }
+
+    /// Maps the file blocks starting from `block` to `device_range`.
+    ///
+    /// The file blocks must not be mapped before.
+    pub fn insert(&mut self, block: Ext2Bid, device_range: Range<Ext2Bid>) {
+        debug_assert!(
+            self.holes(block..block + device_range.len() as Ext2Bid)
+                == [block..block + device_range.len() as Ext2Bid]
+        );
+
+        let mut idx = self.extents.partition_point(|extent| extent.block < block);
+        let mut block = block;
+        let mut device_range = device_range;
+        while !device_range.is_empty() {
+            let len = (device_range.len() as Ext2Bid).min(MAX_INIT_LEN);
+            let extent = Extent {
+                block,
+                len,
+                start: device_range.start,
+                is_unwritten: false,
+            };
+            self.extents.insert(idx, extent);
+            idx = self.merge_with_prev(idx) + 1;
+            block += len;
+            device_range.start += len;
+        }
+        self.is_dirty = true;
+    }
+
+    /// Marks the unwritten file block `block` as written, and returns its device block.
+    ///
+    /// Returns `None` if the block is not an unwritten one.
+    pub fn mark_written(&mut self, block: Ext2Bid) -> Option<Ext2Bid> {
+        let idx = self.extents.partition_point(|extent| extent.end() <= block);
+        let extent = *self.extents.get(idx)?;
+        if extent.block > block || !extent.is_unwritten {
+            return None;
+        }
+
+        let device_bid = extent.start + (block - extent.block);
+        let mut split = Vec::with_capacity(3);
+        if block > extent.block {
+            split.push(Extent {
+                len: block - extent.block,
+                ..extent
+            });
+        }
+        let written_idx = idx + split.len();
+        split.push(Extent {
+            block,
+            len: 1,
+            start: device_bid,
+            is_unwritten: false,
+        });
+        if block + 1 < extent.end() {
+            split.push(Extent {
+                block: block + 1,
+                len: extent.end() - block - 1,
+                start: device_bid + 1,
+                is_unwritten: true,
+            });
+        }
+        self.extents.splice(idx..idx + 1, split);
+
+        let written_idx = self.merge_with_prev(written_idx);
+        if written_idx + 1 < self.extents.len() {
+            self.merge_with_prev(written_idx + 1);
+        }
+        self.is_dirty = true;
+        Some(device_bid)
+    }
+
+    /// Merges the extent at `idx` into the previous one if possible,
+    /// and returns the index of the extent containing its blocks.
+    fn merge_with_prev(&mut self, idx: usize) -> usize {
+        if idx == 0 || !self.extents[idx - 1].is_mergeable(&self.extents[idx]) {
+            return idx;
+        }
+        let extent = self.extents.remove(idx);
+        self.extents[idx - 1].len += extent.len;
+        idx - 1
+    }
+
+    /// Unmaps the file blocks starting from `nblocks`, and returns the freed device blocks.
+    pub fn truncate(&mut self, nblocks: Ext2Bid) -> Vec<Range<Ext2Bid>> {
+        let mut freed_ranges = Vec::new();
+        while let Some(last) = self.extents.last_mut() {
+            if last.end() <= nblocks {
+                break;
+            }
+            if last.block >= nblocks {
+                freed_ranges.push(last.device_range());
+                self.extents.pop();
+            } else {
+                let kept_len = nblocks - last.block;
+                freed_ranges.push(last.start + kept_len..last.start + last.len);
+                last.len = kept_len;
+            }
+            self.is_dirty = true;
+        }
+        freed_ranges
+    }
+
+    /// Writes the tree back, and returns the block pointers containing the new root.
+    ///
+    /// The nodes are allocated by `alloc_node`, freed by `free_node`
+    /// and written by `write_node`.
+    pub fn persist(
+        &mut self,
+        alloc_node: &mut dyn FnMut() -> Result<Ext2Bid>,
+        free_node: &mut dyn FnMut(Ext2Bid) -> Result<()>,
+        write_node: &mut dyn FnMut(Ext2Bid, &[u8]) -> Result<()>,
+    ) -> Result<BlockPtrs> {
+        // Computes the number of nodes at each level except the root, from the leaves up.
+        let mut nnodes_per_level = Vec::new();
+        let mut nentries = self.extents.len();
+        while nentries > ROOT_MAX_ENTRIES {
+            nentries = nentries.div_ceil(NODE_MAX_ENTRIES);
+            nnodes_per_level.push(nentries);
+        }
+        let depth = nnodes_per_level.len() as u16;
+        if depth > MAX_DEPTH {
+            return_errno_with_message!(Errno::EFBIG, "too many extents");
+        }
+
+        let nnodes: usize = nnodes_per_level.iter().sum();
+        while self.node_bids.len() > nnodes {
+            free_node(self.node_bids.pop().unwrap())?;
+        }
+        while self.node_bids.len() < nnodes {
+            self.node_bids.push(alloc_node()?);
+        }
+
+        let mut block_ptrs = BlockPtrs::default();
+        if depth == 0 {
+            encode_leaf(block_ptrs.as_bytes_mut(), ROOT_MAX_ENTRIES, &self.extents);
+            self.is_dirty = false;
+            return Ok(block_ptrs);
+        }
+
+        let mut node_bids = self.node_bids.iter().copied();
+        let mut node = vec![0u8; BLOCK_SIZE];
+        // The first file block and the device block of each node in the current level.
+        let mut level = Vec::with_capacity(nnodes_per_level[0]);
+        for extents in self.extents.chunks(NODE_MAX_ENTRIES) {
+            let bid = node_bids.next().unwrap();
+            node.fill(0);
+            encode_leaf(&mut node, NODE_MAX_ENTRIES, extents);
+            self.write_node_with_checksum(bid, &mut node, write_node)?;
+            level.push((extents[0].block, bid));
+        }
+        for node_depth in 1..depth {
+            let mut upper_level = Vec::with_capacity(nnodes_per_level[node_depth as usize]);
+            for children in level.chunks(NODE_MAX_ENTRIES) {
+                let bid = node_bids.next().unwrap();
+                node.fill(0);
+                encode_index(&mut node, NODE_MAX_ENTRIES, node_depth, children);
+                self.write_node_with_checksum(bid, &mut node, write_node)?;
+                upper_level.push((children[0].0, bid));
+            }
+            level = upper_level;
+        }
+        encode_index(block_ptrs.as_bytes_mut(), ROOT_MAX_ENTRIES, depth, &level);
+
+        self.is_dirty = false;
+        Ok(block_ptrs)
+    }
+
+    fn write_node_with_checksum(
+        &self,
+        bid: Ext2Bid,
+        node: &mut [u8],
+        write_node: &mut dyn FnMut(Ext2Bid, &[u8]) -> Result<()>,
+    ) -> Result<()> {
+        if let Some(csum_seed) = self.csum_seed {
+            let tail_offset = HEADER_LEN + NODE_MAX_ENTRIES * ENTRY_LEN;
+            let csum = crc32c(csum_seed, &node[..tail_offset]);
+            node[tail_offset..tail_offset + TAIL_LEN].copy_from_slice(&csum.to_le_bytes());
+        }
+        write_node(bid, node)
+    }
+}
+
+fn encode_leaf(node: &mut [u8], max_entries: usize, extents: &[Extent]) {
+    let header = RawExtentHeader::new(extents.len(), max_entries, 0);
+    node[..HEADER_LEN].copy_from_slice(header.as_bytes());
+    for (idx, extent) in extents.iter().enumerate() {
+        let offset = HEADER_LEN + idx * ENTRY_LEN;
+        node[offset..offset + ENTRY_LEN].copy_from_slice(RawExtent::from(extent).as_bytes());
+    }
+}
+
+fn encode_index(node: &mut [u8], max_entries: usize, depth: u16, children: &[(Ext2Bid, Ext2Bid)]) {
+    let header = RawExtentHeader::new(children.len(), max_entries, depth);
+    node[..HEADER_LEN].copy_from_slice(header.as_bytes());
+    for (idx, &(block, leaf)) in children.iter().enumerate() {
+        let raw_index = RawExtentIndex {
+            block,
+            leaf_lo: leaf,
+            leaf_hi: 0,
+            unused: 0,
+        };
+        let offset = HEADER_LEN + idx * ENTRY_LEN;
+        node[offset..offset + ENTRY_LEN].copy_from_slice(raw_index.as_bytes());
+    }
+}
+
+/// The header of an extent tree node.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct RawExtentHeader {
+    magic: u16,
+    /// Number of valid entries following the header.
+    entries: u16,
+    /// Maximum number of entries that could follow the header.
+    max: u16,
+    /// Depth of this node in the tree, where the leaves have a depth of 0.
+    depth: u16,
+    generation: u32,
+}
+
+impl RawExtentHeader {
+    fn new(entries: usize, max: usize, depth: u16) -> Self {
+        Self {
+            magic: EXTENT_MAGIC,
+            entries: entries as u16,
+            max: max as u16,
+            depth,
+            generation: 0,
+        }
+    }
+}
+
+/// The entry of an internal node of the extent tree.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct RawExtentIndex {
+    /// The first file block covered by the child.
+    block: u32,
+    /// The device block of the child.
+    leaf_lo: u32,
+    leaf_hi: u16,
+    unused: u16,
+}
+
+/// The entry of a leaf of the extent tree.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct RawExtent {
+    /// The first file block.
+    block: u32,
+    /// Number of blocks, where a value greater than 32768 indicates an unwritten extent.
+    len: u16,
+    start_hi: u16,
+    start_lo: u32,
+}
+
+impl RawExtent {
+    fn to_extent(self) -> Result<Extent> {
+        if self.start_hi != 0 {
+            return_errno_with_message!(Errno::EINVAL, "extent beyond 2^32 blocks");
+        }
+        let (len, is_unwritten) = if self.len as Ext2Bid > MAX_INIT_LEN {
+            (self.len as Ext2Bid - MAX_INIT_LEN, true)
+        } else {
+            (self.len as Ext2Bid, false)
+        };
+        if len == 0 || self.block.checked_add(len).is_none() {
+            return_errno_with_message!(Errno::EUCLEAN, "invalid extent");
+        }
+        Ok(Extent {
+            block: self.block,
+            len,
+            start: self.start_lo,
+            is_unwritten,
+        })
+    }
+}
+
+impl From<&Extent> for RawExtent {
+    fn from(extent: &Extent) -> Self {
+        let len = if extent.is_unwritten {
+            extent.len + MAX_INIT_LEN
+        } else {
+            extent.len
+        };
+        Self {
+            block: extent.block,
+            len: len as u16,
+            start_hi: 0,
+            start_lo: extent.start,
+        }
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn lookup_with_holes() {
+        let mut tree = ExtentTree::new(None);
+        tree.insert(2, 100..104);
+        tree.insert(10, 200..201);
+        assert_eq!(
+            tree.lookup(0..12),
+            vec![
+                BlockMapping::Zeroed(2),
+                BlockMapping::Mapped(100..104),
+                BlockMapping::Zeroed(4),
+                BlockMapping::Mapped(200..201),
+                BlockMapping::Zeroed(1),
+            ]
+        );
+        assert_eq!(tree.holes(0..12), vec![0..2, 6..10, 11..12]);
+    }
+
+    #[ktest]
+    fn insert_merges_contiguous_extents() {
+        let mut tree = ExtentTree::new(None);
+        tree.insert(0, 100..102);
+        tree.insert(2, 102..105);
+        assert_eq!(tree.extents.len(), 1);
+        assert_eq!(tree.lookup(0..5), vec![BlockMapping::Mapped(100..105)]);
+    }
+
+    #[ktest]
+    fn write_unwritten_extent() {
+        let mut tree = ExtentTree::new(None);
+        tree.extents.push(Extent {
+            block: 0,
+            len: 4,
+            start: 100,
+            is_unwritten: true,
+        });
+        assert_eq!(tree.mark_written(2), Some(102));
+        assert_eq!(
+            tree.lookup(0..4),
+            vec![
+                BlockMapping::Zeroed(2),
+                BlockMapping::Mapped(102..103),
+                BlockMapping::Zeroed(1),
+            ]
+        );
+        assert_eq!(tree.mark_written(2), None);
+    }
+
+    #[ktest]
+    fn truncate_frees_blocks() {
+        let mut tree = ExtentTree::new(None);
+        tree.insert(0, 100..104);
+        tree.insert(8, 200..202);
+        assert_eq!(tree.truncate(2), vec![200..202, 102..104]);
+        assert_eq!(tree.lookup(0..2), vec![BlockMapping::Mapped(100..102)]);
+    }
+
+    #[ktest]
+    fn persist_and_load() {
+        let mut tree = ExtentTree::new(Some(0));
+        for idx in 0..1000 {
+            // Leaves a hole between the extents, so that they are not merged.
+            tree.insert(idx * 2, 1000 + idx * 2..1001 + idx * 2);
+        }
+
+        let mut nodes = BTreeMap::new();
+        let mut next_bid = 10;
+        let root = tree
+            .persist(
+                &mut || {
+                    next_bid += 1;
+                    Ok(next_bid)
+                },
+                &mut |_| Ok(()),
+                &mut |bid, node| {
+                    nodes.insert(bid, node.to_vec());
+                    Ok(())
+                },
+            )
+            .unwrap();
+        assert!(!tree.is_dirty());
+        assert_eq!(nodes.len(), 3);
+
+        let loaded = ExtentTree::load(&root, None, &mut |bid, buf| {
+            buf.copy_from_slice(&nodes[&bid]);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(loaded.extents, tree.extents);
+        assert_eq!(loaded.node_bids, tree.node_bids);
+    }
+}
@@ -3,9 +3,13 @@
 #![expect(dead_code)]
 
 use super::{
-    block_group::{BlockGroup, RawGroupDescriptor},
-    block_ptr::Ext2Bid,
-    inode::{FilePerm, Inode, InodeDesc, RawInode},
+    block_group::{group_desc_checksum, BlockGroup, RawGroupDescriptor},
+    block_ptr::{Ext2Bid, BID_SIZE, DB_INDIRECT, INDIRECT, MAX_DIRECT_BLOCKS, TB_INDIRECT},
+    csum::crc32c,
+    extent::{BlockMapping, ExtentTree},
+    htree::HashParams,
+    inode::{FileFlags, FilePerm, Inode, InodeDesc, RawInode},
+    journal::Journal,
     prelude::*,
     super_block::{RawSuperBlock, SuperBlock, SUPER_BLOCK_OFFSET},
 };
@@ -23,41 +27,46 @@ pub struct Ext2 {
     blocks_per_group: Ext2Bid,
     inode_size: usize,
     block_size: usize,
+    desc_size: usize,
     group_descriptors_segment: USegment,
+    /// The UUID of the filesystem.
+    uuid: [u8; 16],
+    /// The checksum seed, if the metadata checksums are enabled.
+    csum_seed: Option<u32>,
+    /// The parameters of the directory hashes, if the hash tree indexes are enabled.
+    hash_params: Option<HashParams>,
+    /// The journal, through which all the metadata are written.
+    journal: Option<Journal>,
     self_ref: Weak<Self>,
 }
 
 impl Ext2 {
     /// Opens and loads an Ext2 from the `block_device`.
+    ///
+    /// If the filesystem has a journal, the journal is recovered first.
     pub fn open(block_device: Arc<dyn BlockDevice>) -> Result<Arc<Self>> {
-        // Load the superblock
-        // TODO: if the main superblock is corrupted, should we load the backup?
-        let super_block = {
-            let raw_super_block = block_device.read_val::<RawSuperBlock>(SUPER_BLOCK_OFFSET)?;
-            SuperBlock::try_from(raw_super_block)?
-        };
-        assert_eq!(
-            super_block.block_size(),
-            BLOCK_SIZE,
-            "currently only support 4096-byte block size"
-        );
-
-        let group_descriptors_segment: USegment = {
-            let npages = ((super_block.block_groups_count() as usize)
-                * core::mem::size_of::<RawGroupDescriptor>())
-            .div_ceil(BLOCK_SIZE);
-            let segment = FrameAllocOptions::new()
-                .zeroed(false)
-                .alloc_segment(npages)?;
-            let bio_segment =
-                BioSegment::new_from_segment(segment.clone().into(), BioDirection::FromDevice);
-            match block_device.read_blocks(super_block.group_descriptors_bid(0), bio_segment)? {
-                BioStatus::Complete => (),
-                err_status => {
-                    return Err(Error::from(err_status));
-                }
+        let (mut super_block, mut group_descriptors_segment) = load_metadata(&block_device)?;
+
+        let journal = if super_block.has_journal() {
+            let journal_blocks =
+                journal_blocks(&block_device, &super_block, &group_descriptors_segment)?;
+            let journal = Journal::load(block_device.clone(), journal_blocks)?;
+            if super_block.needs_recovery() {
+                journal.recover()?;
+                // The metadata may have been changed by the replay.
+                (super_block, group_descriptors_segment) = load_metadata(&block_device)?;
             }
-            segment.into()
+
+            // Each transaction is checkpointed as soon as it commits, so the flag only
+            // guards against crashes in the middle of a commit.
+            super_block.set_needs_recovery();
+            let mut raw_super_block = RawSuperBlock::from(&super_block);
+            raw_super_block.update_checksum();
+            block_device.write_bytes(SUPER_BLOCK_OFFSET, raw_super_block.as_bytes())?;
+            block_device.sync()?;
+            Some(journal)
+        } else {
+            None
         };
 
         // Load the block groups information
@@ -80,11 +89,16 @@ impl Ext2 {
             Ok(block_groups)
         };
 
+        let hash_params = super_block.has_dir_index().then(|| HashParams {
+            seed: *super_block.hash_seed(),
+            is_unsigned: super_block.is_hash_unsigned(),
+        });
         let ext2 = Arc::new_cyclic(|weak_ref| Self {
             inodes_per_group: super_block.inodes_per_group(),
             blocks_per_group: super_block.blocks_per_group(),
             inode_size: super_block.inode_size(),
             block_size: super_block.block_size(),
+            desc_size: super_block.desc_size(),
             block_groups: load_block_groups(
                 weak_ref.clone(),
                 block_device.as_ref(),
@@ -92,6 +106,10 @@ impl Ext2 {
             )
             .unwrap(),
             block_device,
+            uuid: *super_block.uuid(),
+            csum_seed: super_block.csum_seed(),
+            hash_params,
+            journal,
             super_block: RwMutex::new(Dirty::new(super_block)),
             group_descriptors_segment,
             self_ref: weak_ref.clone(),
@@ -129,6 +147,29 @@ impl Ext2 {
         self.super_block.read()
     }
 
+    /// Returns the checksum seed of the filesystem, if the metadata checksums are enabled.
+    pub(super) fn csum_seed(&self) -> Option<u32> {
+        self.csum_seed
+    }
+
+    /// Returns the checksum seed of the inode numbered `ino`, if the metadata checksums are enabled.
+    pub(super) fn inode_csum_seed(&self, ino: u32, generation: u32) -> Option<u32> {
+        self.csum_seed.map(|csum_seed| {
+            let csum = crc32c(csum_seed, &ino.to_le_bytes());
+            crc32c(csum, &generation.to_le_bytes())
+        })
+    }
+
+    /// Returns the parameters of the directory hashes, if the hash tree indexes are enabled.
+    pub(super) fn hash_params(&self) -> Option<HashParams> {
+        self.hash_params
+    }
+
+    /// Returns whether the filesystem has a journal.
+    pub fn has_journal(&self) -> bool {
+        self.journal.is_some()
+    }
+
     /// Returns the root inode.
     pub fn root_inode(&self) -> Result<Arc<Inode>> {
         self.lookup_inode(ROOT_INO)
@@ -150,11 +191,20 @@ impl Ext2 {
     ) -> Result<Arc<Inode>> {
         let (block_group_idx, ino) =
             self.alloc_ino(dir_block_group_idx, inode_type == InodeType::Dir)?;
+        let block_group = &self.block_groups[block_group_idx];
+        block_group.init_raw_inode(
+            self.inode_idx(ino),
+            self.super_block.read().new_inode_extra_isize(),
+        );
         let inode = {
-            let inode_desc = InodeDesc::new(inode_type, file_perm);
-            Inode::new(ino, block_group_idx, inode_desc, self.self_ref.clone())
+            let mut inode_desc = InodeDesc::new(inode_type, file_perm);
+            if self.super_block.read().has_extents()
+                && matches!(inode_type, InodeType::File | InodeType::Dir)
+            {
+                inode_desc.enable_extents();
+            }
+            Inode::new(ino, block_group_idx, inode_desc, self.self_ref.clone())?
         };
-        let block_group = &self.block_groups[block_group_idx];
         block_group.insert_cache(self.inode_idx(ino), inode.clone());
         Ok(inode)
     }
@@ -210,9 +260,19 @@ impl Ext2 {
         block_group_idx: usize,
         raw_descriptor: &RawGroupDescriptor,
     ) -> Result<()> {
-        let offset = block_group_idx * core::mem::size_of::<RawGroupDescriptor>();
-        self.group_descriptors_segment
-            .write_val(offset, raw_descriptor)?;
+        let offset = block_group_idx * self.desc_size;
+        let len = self
+            .desc_size
+            .min(core::mem::size_of::<RawGroupDescriptor>());
+        let bytes = &raw_descriptor.as_bytes()[..len];
+        self.group_descriptors_segment.write_bytes(offset, bytes)?;
+
+        if self.super_block.read().has_group_desc_csum() {
+            let checksum = group_desc_checksum(&self.uuid, self.csum_seed, block_group_idx, bytes);
+            let checksum_offset = core::mem::offset_of!(RawGroupDescriptor, checksum);
+            self.group_descriptors_segment
+                .write_val(offset + checksum_offset, &checksum)?;
+        }
         Ok(())
     }
 
@@ -295,32 +355,89 @@ impl Ext2 {
             current_range.start += range_in_group.len() as Ext2Bid
         }
 
+        // The freed blocks may be reused for data, which must not be overwritten
+        // by the stale metadata in the journal.
+        if let Some(journal) = self.journal.as_ref() {
+            journal.forget(range);
+        }
+
         Ok(())
     }
 
     /// Reads contiguous blocks starting from the `bid` synchronously.
+    ///
+    /// The blocks in the running transaction of the journal are read from the journal.
     pub(super) fn read_blocks(&self, bid: Ext2Bid, bio_segment: BioSegment) -> Result<()> {
         let status = self
             .block_device
-            .read_blocks(Bid::new(bid as u64), bio_segment)?;
+            .read_blocks(Bid::new(bid as u64), bio_segment.clone())?;
         match status {
-            BioStatus::Complete => Ok(()),
-            err_status => Err(Error::from(err_status)),
+            BioStatus::Complete => (),
+            err_status => return Err(Error::from(err_status)),
         }
+        self.patch_logged_blocks(bid, &bio_segment)
     }
 
     /// Reads contiguous blocks starting from the `bid` asynchronously.
+    ///
+    /// The blocks are read synchronously if some of them are in the running transaction
+    /// of the journal.
     pub(super) fn read_blocks_async(
         &self,
         bid: Ext2Bid,
         bio_segment: BioSegment,
     ) -> Result<BioWaiter> {
+        let range = bid..bid + bio_segment.nblocks() as Ext2Bid;
+        if self
+            .journal
+            .as_ref()
+            .is_some_and(|journal| journal.contains_any(range))
+        {
+            self.read_blocks(bid, bio_segment)?;
+            return Ok(BioWaiter::new());
+        }
+
         let waiter = self
             .block_device
             .read_blocks_async(Bid::new(bid as u64), bio_segment)?;
         Ok(waiter)
     }
 
+    /// Reads the metadata at `offset` of the device into `buf`.
+    pub(super) fn read_metadata_bytes(&self, offset: usize, buf: &mut [u8]) -> Result<()> {
+        self.block_device.read_bytes(offset, buf)?;
+
+        let Some(journal) = self.journal.as_ref() else {
+            return Ok(());
+        };
+        let range = {
+            let start = (offset / BLOCK_SIZE) as Ext2Bid;
+            let end = (offset + buf.len()).div_ceil(BLOCK_SIZE) as Ext2Bid;
+            start..end
+        };
+        for (bid, block) in journal.logged_blocks(range) {
+            let block_offset = bid as usize * BLOCK_SIZE;
+            let start = offset.max(block_offset);
+            let end = (offset + buf.len()).min(block_offset + BLOCK_SIZE);
+            buf[start - offset..end - offset]
+                .copy_from_slice(&block[start - block_offset..end - block_offset]);
+        }
+        Ok(())
+    }
+
+    /// Overwrites the blocks in `bio_segment` starting from the `bid` with the ones
+    /// in the running transaction of the journal.
+    fn patch_logged_blocks(&self, bid: Ext2Bid, bio_segment: &BioSegment) -> Result<()> {
+        let Some(journal) = self.journal.as_ref() else {
+            return Ok(());
+        };
+        let range = bid..bid + bio_segment.nblocks() as Ext2Bid;
+        for (logged_bid, block) in journal.logged_blocks(range) {
+            bio_segment.write_bytes((logged_bid - bid) as usize * BLOCK_SIZE, &block)?;
+        }
+        Ok(())
+    }
+
     /// Writes contiguous blocks starting from the `bid` synchronously.
     pub(super) fn write_blocks(&self, bid: Ext2Bid, bio_segment: BioSegment) -> Result<()> {
         let status = self
@@ -344,6 +461,50 @@ impl Ext2 {
         Ok(waiter)
     }
 
+    /// Writes the metadata blocks starting from the `bid` asynchronously.
+    ///
+    /// If the filesystem has a journal, the blocks are added to the running transaction,
+    /// which reaches the device on the next commit.
+    pub(super) fn write_metadata_blocks_async(
+        &self,
+        bid: Ext2Bid,
+        bio_segment: BioSegment,
+    ) -> Result<BioWaiter> {
+        let Some(journal) = self.journal.as_ref() else {
+            return self.write_blocks_async(bid, bio_segment);
+        };
+
+        let mut buf = vec![0u8; bio_segment.nbytes()];
+        bio_segment.read_bytes(0, &mut buf)?;
+        journal.write_bytes(bid as usize * BLOCK_SIZE, &buf)?;
+        Ok(BioWaiter::new())
+    }
+
+    /// Writes the metadata `bytes` at `offset` of the device asynchronously.
+    ///
+    /// If the filesystem has a journal, the bytes are added to the running transaction,
+    /// which reaches the device on the next commit.
+    pub(super) fn write_metadata_bytes_async(
+        &self,
+        offset: usize,
+        bytes: &[u8],
+    ) -> Result<BioWaiter> {
+        let Some(journal) = self.journal.as_ref() else {
+            return Ok(self.block_device.write_bytes_async(offset, bytes)?);
+        };
+
+        journal.write_bytes(offset, bytes)?;
+        Ok(BioWaiter::new())
+    }
+
+    /// Commits the running transaction of the journal, if any.
+    pub fn commit_journal(&self) -> Result<()> {
+        match self.journal.as_ref() {
+            Some(journal) => journal.commit(),
+            None => Ok(()),
+        }
+    }
+
     /// Writes back the metadata to the block device.
     pub fn sync_metadata(&self) -> Result<()> {
         // If the superblock is clean, the block groups must be clean.
@@ -359,17 +520,17 @@ impl Ext2 {
 
         // Writes back the main superblock and group descriptor table.
         let mut bio_waiter = BioWaiter::new();
-        let raw_super_block = RawSuperBlock::from((*super_block).deref());
+        let mut raw_super_block = RawSuperBlock::from((*super_block).deref());
+        raw_super_block.update_checksum();
         bio_waiter.concat(
-            self.block_device
-                .write_bytes_async(SUPER_BLOCK_OFFSET, raw_super_block.as_bytes())?,
+            self.write_metadata_bytes_async(SUPER_BLOCK_OFFSET, raw_super_block.as_bytes())?,
         );
         let group_descriptors_bio_segment = BioSegment::new_from_segment(
             self.group_descriptors_segment.clone(),
             BioDirection::ToDevice,
         );
-        bio_waiter.concat(self.block_device.write_blocks_async(
-            super_block.group_descriptors_bid(0),
+        bio_waiter.concat(self.write_metadata_blocks_async(
+            super_block.group_descriptors_bid(0).to_raw() as Ext2Bid,
             group_descriptors_bio_segment.clone(),
         )?);
         bio_waiter
@@ -383,12 +544,13 @@ impl Ext2 {
             if super_block.is_backup_group(idx as usize) {
                 let mut bio_waiter = BioWaiter::new();
                 raw_super_block_backup.block_group_idx = idx as u16;
-                bio_waiter.concat(self.block_device.write_bytes_async(
+                raw_super_block_backup.update_checksum();
+                bio_waiter.concat(self.write_metadata_bytes_async(
                     super_block.bid(idx as usize).to_offset(),
                     raw_super_block_backup.as_bytes(),
                 )?);
-                bio_waiter.concat(self.block_device.write_blocks_async(
-                    super_block.group_descriptors_bid(idx as usize),
+                bio_waiter.concat(self.write_metadata_blocks_async(
+                    super_block.group_descriptors_bid(idx as usize).to_raw() as Ext2Bid,
                     group_descriptors_bio_segment.clone(),
                 )?);
                 bio_waiter.wait().ok_or_else(|| {
@@ -438,3 +600,136 @@ impl Ext2 {
         bid % self.blocks_per_group
     }
 }
+
+/// Loads the superblock and the group descriptor table from the `block_device`.
+fn load_metadata(block_device: &Arc<dyn BlockDevice>) -> Result<(SuperBlock, USegment)> {
+    // Load the superblock
+    // TODO: if the main superblock is corrupted, should we load the backup?
+    let super_block = {
+        let raw_super_block = block_device.read_val::<RawSuperBlock>(SUPER_BLOCK_OFFSET)?;
+        SuperBlock::try_from(raw_super_block)?
+    };
+    if super_block.block_size() != BLOCK_SIZE {
+        return_errno_with_message!(Errno::EINVAL, "currently only support 4096-byte block size");
+    }
+
+    let group_descriptors_segment = {
+        let npages = ((super_block.block_groups_count() as usize) * super_block.desc_size())
+            .div_ceil(BLOCK_SIZE);
+        let segment = FrameAllocOptions::new()
+            .zeroed(false)
+            .alloc_segment(npages)?;
+        let bio_segment =
+            BioSegment::new_from_segment(segment.clone().into(), BioDirection::FromDevice);
+        match block_device.read_blocks(super_block.group_descriptors_bid(0), bio_segment)? {
+            BioStatus::Complete => (),
+            err_status => {
+                return Err(Error::from(err_status));
+            }
+        }
+        segment.into()
+    };
+
+    Ok((super_block, group_descriptors_segment))
+}
+
+/// Returns the device blocks of the journal, in the order of the blocks in the journal.
+fn journal_blocks(
+    block_device: &Arc<dyn BlockDevice>,
+    super_block: &SuperBlock,
+    group_descriptors_segment: &USegment,
+) -> Result<Vec<Ext2Bid>> {
+    let ino = super_block.journal_ino();
+    if ino == 0 || ino > super_block.total_inodes() {
+        return_errno_with_message!(Errno::EINVAL, "the journal is not in an inode");
+    }
+
+    let raw_inode = {
+        let block_group_idx = ((ino - 1) / super_block.inodes_per_group()) as usize;
+        let inode_idx = ((ino - 1) % super_block.inodes_per_group()) as usize;
+        let raw_descriptor = RawGroupDescriptor::load(
+            group_descriptors_segment,
+            block_group_idx,
+            super_block.desc_size(),
+        );
+        let offset =
+            raw_descriptor.inode_table as usize * BLOCK_SIZE + inode_idx * super_block.inode_size();
+        block_device.read_val::<RawInode>(offset)?
+    };
+    let nblocks = (((raw_inode.size_high as usize) << 32 | raw_inode.size_low as usize)
+        / BLOCK_SIZE) as Ext2Bid;
+
+    let mut read_block = |bid: Ext2Bid, buf: &mut [u8]| -> Result<()> {
+        block_device.read_bytes(bid as usize * BLOCK_SIZE, buf)?;
+        Ok(())
+    };
+
+    let mut blocks = Vec::with_capacity(nblocks as usize);
+    if raw_inode.flags & FileFlags::EXTENTS.bits() != 0 {
+        let csum_seed = super_block.csum_seed().map(|csum_seed| {
+            let csum = crc32c(csum_seed, &ino.to_le_bytes());
+            crc32c(csum, &raw_inode.generation.to_le_bytes())
+        });
+        let extent_tree = ExtentTree::load(&raw_inode.block_ptrs, csum_seed, &mut read_block)?;
+        for mapping in extent_tree.lookup(0..nblocks) {
+            let BlockMapping::Mapped(device_range) = mapping else {
+                return_errno_with_message!(Errno::EINVAL, "the journal has holes");
+            };
+            blocks.extend(device_range);
+        }
+        return Ok(blocks);
+    }
+
+    // Walks the block pointers, where `depth` is the level of indirection of `bid`.
+    fn walk(
+        bid: Ext2Bid,
+        depth: usize,
+        nblocks: Ext2Bid,
+        blocks: &mut Vec<Ext2Bid>,
+        read_block: &mut dyn FnMut(Ext2Bid, &mut [u8]) -> Result<()>,
+    ) -> Result<()> {
+        if blocks.len() as Ext2Bid >= nblocks {
+            return Ok(());
+        }
+        if bid == 0 {
+            return_errno_with_message!(Errno::EINVAL, "the journal has holes");
+        }
+        if depth == 0 {
+            blocks.push(bid);
+            return Ok(());
+        }
+
+        let mut buf = vec![0u8; BLOCK_SIZE];
+        read_block(bid, &mut buf)?;
+        for bytes in buf.chunks_exact(BID_SIZE) {
+            let child = Ext2Bid::from_le_bytes(bytes.try_into().unwrap());
+            walk(child, depth - 1, nblocks, blocks, read_block)?;
+        }
+        Ok(())
+    }
+
+    let block_ptrs = &raw_inode.block_ptrs;
+    for idx in 0..MAX_DIRECT_BLOCKS as usize {
+        walk(
+            block_ptrs.direct(idx),
+            0,
+            nblocks,
+            &mut blocks,
+            &mut read_block,
+        )?;
+    }
+    for (idx, bid) in [
+        (INDIRECT, block_ptrs.indirect()),
+        (DB_INDIRECT, block_ptrs.db_indirect()),
+        (TB_INDIRECT, block_ptrs.tb_indirect()),
+    ] {
+        walk(
+            bid,
+            idx - INDIRECT + 1,
+            nblocks,
+            &mut blocks,
+            &mut read_block,
+        )?;
+    }
+    Ok(blocks)
+}
@@ -0,0 +1,378 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Hash tree indexes of directories.
+//!
+//! An indexed directory is still a valid linear directory. The first block and
+//! the internal nodes of the index look like blocks with no entries to linear
+//! readers, while the index maps the hashes of names to the leaf blocks holding
+//! the entries with these hashes.
+//!
+//! The index is used to speed up lookups only. Before an entry is added, removed
+//! or renamed, the index is dropped and the directory becomes a linear one.
+
+use super::{dir::write_dir_tail, prelude::*};
+
+/// The offset of the index information in the root block.
+const ROOT_INFO_OFFSET: usize = 24;
+/// The offset of the entries in the internal nodes, after a fake directory entry.
+const NODE_ENTRIES_OFFSET: usize = 8;
+const ENTRY_LEN: usize = 8;
+/// The maximum number of levels of internal nodes below the root.
+const MAX_INDIRECT_LEVELS: u8 = 2;
+/// The maximum number of leaf blocks to visit for the colliding hashes.
+const MAX_COLLISION_BLOCKS: usize = 64;
+/// The hash that marks the end of a directory in 32-bit `readdir` offsets.
+const HTREE_EOF_32BIT: u32 = 0x7fff_ffff;
+
+/// The hash algorithms of the directory index.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum HashVersion {
+    Legacy,
+    HalfMd4,
+    Tea,
+}
+
+impl TryFrom<u8> for HashVersion {
+    type Error = crate::error::Error;
+
+    fn try_from(version: u8) -> Result<Self> {
+        // The unsigned variants (3, 4 and 5) are selected by the superblock flags.
+        match version {
+            0 | 3 => Ok(Self::Legacy),
+            1 | 4 => Ok(Self::HalfMd4),
+            2 | 5 => Ok(Self::Tea),
+            _ => return_errno_with_message!(Errno::EINVAL, "unsupported directory hash"),
+        }
+    }
+}
+
+/// The parameters of the directory hashes, which are taken from the superblock.
+#[derive(Clone, Copy, Debug)]
+pub(super) struct HashParams {
+    pub seed: [u32; 4],
+    pub is_unsigned: bool,
+}
+
+/// A node of the index, and the position in its entries.
+#[derive(Clone, Copy, Debug)]
+struct Frame {
+    /// The offset of the entries in the directory.
+    entries_offset: usize,
+    count: usize,
+    at: usize,
+}
+
+/// Looks up the leaf blocks that may contain the entry of `name`.
+///
+/// Returns an error if the index is corrupted or not supported,
+/// in which case the directory should be searched linearly.
+pub(super) fn lookup(
+    page_cache: &PageCache,
+    nblocks: usize,
+    name: &str,
+    params: &HashParams,
+) -> Result<Vec<usize>> {
+    let pages = page_cache.pages();
+    let reserved_zero = pages.read_val::<u32>(ROOT_INFO_OFFSET)?;
+    let hash_version = pages.read_val::<u8>(ROOT_INFO_OFFSET + 4)?;
+    let info_length = pages.read_val::<u8>(ROOT_INFO_OFFSET + 5)?;
+    let indirect_levels = pages.read_val::<u8>(ROOT_INFO_OFFSET + 6)?;
+    if reserved_zero != 0 || info_length != 8 || indirect_levels > MAX_INDIRECT_LEVELS {
+        return_errno_with_message!(Errno::EUCLEAN, "invalid directory index root");
+    }
+    let hash = dir_hash(
+        name.as_bytes(),
+        HashVersion::try_from(hash_version)?,
+        params,
+    );
+
+    let read_frame = |block: usize, entries_offset: usize| -> Result<Frame> {
+        if block >= nblocks {
+            return_errno_with_message!(Errno::EUCLEAN, "invalid directory index block");
+        }
+        let entries_offset = block * BLOCK_SIZE + entries_offset;
+        let limit = pages.read_val::<u16>(entries_offset)? as usize;
+        let count = pages.read_val::<u16>(entries_offset + 2)? as usize;
+        if count == 0
+            || count > limit
+            || entries_offset % BLOCK_SIZE + limit * ENTRY_LEN > BLOCK_SIZE
+        {
+            return_errno_with_message!(Errno::EUCLEAN, "invalid directory index node");
+        }
+        Ok(Frame {
+            entries_offset,
+            count,
+            at: 0,
+        })
+    };
+    let entry_hash = |frame: &Frame| -> Result<u32> {
+        Ok(pages.read_val::<u32>(frame.entries_offset + frame.at * ENTRY_LEN)?)
+    };
+    let entry_block = |frame: &Frame| -> Result<usize> {
+        let block = pages.read_val::<u32>(frame.entries_offset + frame.at * ENTRY_LEN + 4)?;
+        Ok((block & 0x0fff_ffff) as usize)
+    };
+
+    // Walks from the root down to the leaf which the hash belongs to.
+    let mut frames = Vec::with_capacity(indirect_levels as usize + 1);
+    let mut frame = read_frame(0, ROOT_INFO_OFFSET + info_length as usize)?;
+    loop {
+        // Finds the last entry whose hash is not greater than the hash.
+        // The first entry has no hash, which covers the hashes lower than the others.
+        let (mut low, mut high) = (1, frame.count);
+        while low < high {
+            let mid = (low + high) / 2;
+            let mid_hash = entry_hash(&Frame { at: mid, ..frame })?;
+            if mid_hash > hash {
+                high = mid;
+            } else {
+                low = mid + 1;
+            }
+        }
+        frame.at = low - 1;
+        frames.push(frame);
+        if frames.len() > indirect_levels as usize {
+            break;
+        }
+        frame = read_frame(entry_block(&frame)?, NODE_ENTRIES_OFFSET)?;
+    }
+
+    let mut leaf_blocks = vec![entry_block(frames.last().unwrap())?];
+    // The entries with the same hash may continue in the next leaves,
+    // whose hashes in the index have the lowest bit set.
+    while leaf_blocks.len() < MAX_COLLISION_BLOCKS {
+        let Some(level) = (0..frames.len())
+            .rev()
+            .find(|&level| frames[level].at + 1 < frames[level].count)
+        else {
+            break;
+        };
+        frames[level].at += 1;
+        if entry_hash(&frames[level])? & !1 != hash {
+            break;
+        }
+        for level in level + 1..frames.len() {
+            frames[level] = read_frame(entry_block(&frames[level - 1])?, NODE_ENTRIES_OFFSET)?;
+        }
+        let block = entry_block(frames.last().unwrap())?;
+        if block >= nblocks {
+            return_errno_with_message!(Errno::EUCLEAN, "invalid directory leaf block");
+        }
+        leaf_blocks.push(block);
+    }
+
+    if leaf_blocks[0] >= nblocks {
+        return_errno_with_message!(Errno::EUCLEAN, "invalid directory leaf block");
+    }
+    Ok(leaf_blocks)
+}
+
+/// Drops the index of the directory, turning the index blocks into empty directory blocks.
+///
+/// The directory blocks end with a tail of `tail_len` bytes.
+/// The caller should clear the `INDEX_DIR` flag of the inode.
+pub(super) fn remove_index(page_cache: &PageCache, nblocks: usize, tail_len: usize) -> Result<()> {
+    let pages = page_cache.pages();
+    let info_length = pages.read_val::<u8>(ROOT_INFO_OFFSET + 5)? as usize;
+    let indirect_levels = pages.read_val::<u8>(ROOT_INFO_OFFSET + 6)?;
+
+    // Collects the internal nodes level by level. The stale index is harmless
+    // once the flag is cleared, so the corrupted parts are simply skipped.
+    let mut node_blocks = Vec::new();
+    let mut level_entries = vec![ROOT_INFO_OFFSET + info_length];
+    for _ in 0..indirect_levels.min(MAX_INDIRECT_LEVELS) {
+        let mut next_level_entries = Vec::new();
+        for entries_offset in level_entries {
+            let Ok(count) = pages.read_val::<u16>(entries_offset + 2) else {
+                continue;
+            };
+            let count =
+                (count as usize).min((BLOCK_SIZE - entries_offset % BLOCK_SIZE) / ENTRY_LEN);
+            for idx in 0..count {
+                let block = pages.read_val::<u32>(entries_offset + idx * ENTRY_LEN + 4)?;
+                let block = (block & 0x0fff_ffff) as usize;
+                if block != 0 && block < nblocks && !node_blocks.contains(&block) {
+                    node_blocks.push(block);
+                    next_level_entries.push(block * BLOCK_SIZE + NODE_ENTRIES_OFFSET);
+                }
+            }
+        }
+        level_entries = next_level_entries;
+    }
+
+    // The ".." entry covers the rest of the root block.
+    let dotdot_offset = 12;
+    pages.write_val(
+        dotdot_offset + 4,
+        &((BLOCK_SIZE - dotdot_offset - tail_len) as u16),
+    )?;
+    pages.write_bytes(
+        ROOT_INFO_OFFSET,
+        &vec![0u8; BLOCK_SIZE - ROOT_INFO_OFFSET - tail_len],
+    )?;
+    if tail_len > 0 {
+        write_dir_tail(page_cache, 0)?;
+    }
+
+    // The internal nodes become blocks with a single unused entry.
+    for block in node_blocks {
+        let offset = block * BLOCK_SIZE;
+        pages.write_bytes(offset, &vec![0u8; BLOCK_SIZE - tail_len])?;
+        pages.write_val(offset + 4, &((BLOCK_SIZE - tail_len) as u16))?;
+        if tail_len > 0 {
+            write_dir_tail(page_cache, offset)?;
+        }
+    }
+    Ok(())
+}
+
+/// Computes the hash of the directory entry name.
+fn dir_hash(name: &[u8], version: HashVersion, params: &HashParams) -> u32 {
+    let mut buf = if params.seed.iter().any(|&word| word != 0) {
+        params.seed
+    } else {
+        [0x6745_2301, 0xefcd_ab89, 0x98ba_dcfe, 0x1032_5476]
+    };
+
+    let hash = match version {
+        HashVersion::Legacy => dx_hack_hash(name, params.is_unsigned),
+        HashVersion::HalfMd4 => {
+            for chunk_start in (0..name.len()).step_by(32) {
+                let input = str2hashbuf(&name[chunk_start..], 8, params.is_unsigned);
+                half_md4_transform(&mut buf, &input);
+            }
+            buf[1]
+        }
+        HashVersion::Tea => {
+            for chunk_start in (0..name.len()).step_by(16) {
+                let input = str2hashbuf(&name[chunk_start..], 4, params.is_unsigned);
+                tea_transform(&mut buf, &input);
+            }
+            buf[0]
+        }
+    };
+
+    let hash = hash & !1;
+    if hash == HTREE_EOF_32BIT << 1 {
+        (HTREE_EOF_32BIT - 1) << 1
+    } else {
+        hash
+    }
+}
+
+fn char_value(byte: u8, is_unsigned: bool) -> u32 {
+    if is_unsigned {
+        byte as u32
+    } else {
+        byte as i8 as i32 as u32
+    }
+}
+
+/// The legacy hash.
+fn dx_hack_hash(name: &[u8], is_unsigned: bool) -> u32 {
+    let (mut hash0, mut hash1) = (0x12a3_fe2d_u32, 0x37ab_e8f9_u32);
+    for &byte in name {
+        let mut hash =
+            hash1.wrapping_add(hash0 ^ char_value(byte, is_unsigned).wrapping_mul(7152373));
+        if hash & 0x8000_0000 != 0 {
+            hash = hash.wrapping_sub(0x7fff_ffff);
+        }
+        hash1 = hash0;
+        hash0 = hash;
+    }
+    hash0 << 1
+}
+
+/// Packs the remaining name `msg` into `num` words, padded with its length.
+fn str2hashbuf(msg: &[u8], num: usize, is_unsigned: bool) -> [u32; 8] {
+    let len = msg.len() as u32;
+    let mut pad = len | (len << 8);
+    pad |= pad << 16;
+
+    let mut buf = [pad; 8];
+    let mut val = pad;
+    let mut idx = 0;
+    for (i, &byte) in msg.iter().take(num * 4).enumerate() {
+        val = char_value(byte, is_unsigned).wrapping_add(val << 8);
+        if i % 4 == 3 {
+            buf[idx] = val;
+            idx += 1;
+            val = pad;
+        }
+    }
+    if idx < num {
+        buf[idx] = val;
+    }
+    buf
+}
+
+fn half_md4_transform(buf: &mut [u32; 4], input: &[u32; 8]) {
+    const K1: u32 = 0;
+    const K2: u32 = 0x5a82_7999;
+    const K3: u32 = 0x6ed9_eba1;
+
+    let f = |x: u32, y: u32, z: u32| z ^ (x & (y ^ z));
+    let g = |x: u32, y: u32, z: u32| (x & y).wrapping_add((x ^ y) & z);
+    let h = |x: u32, y: u32, z: u32| x ^ y ^ z;
+    let round = |func: &dyn Fn(u32, u32, u32) -> u32,
+                 a: &mut u32,
+                 b: u32,
+                 c: u32,
+                 d: u32,
+                 x: u32,
+                 s: u32| {
+        *a = a.wrapping_add(func(b, c, d)).wrapping_add(x).rotate_left(s);
+    };
+
+    let [mut a, mut b, mut c, mut d] = *buf;
+
+    round(&f, &mut a, b, c, d, input[0].wrapping_add(K1), 3);
+    round(&f, &mut d, a, b, c, input[1].wrapping_add(K1), 7);
+    round(&f, &mut c, d, a, b, input[2].wrapping_add(K1), 11);
+    round(&f, &mut b, c, d, a, input[3].wrapping_add(K1), 19);
+    round(&f, &mut a, b, c, d, input[4].wrapping_add(K1), 3);
+    round(&f, &mut d, a, b, c, input[5].wrapping_add(K1), 7);
+    round(&f, &mut c, d, a, b, input[6].wrapping_add(K1), 11);
+    round(&f, &mut b, c, d, a, input[7].wrapping_add(K1), 19);
+
+    round(&g, &mut a, b, c, d, input[1].wrapping_add(K2), 3);
+    round(&g, &mut d, a, b, c, input[3].wrapping_add(K2), 5);
+    round(&g, &mut c, d, a, b, input[5].wrapping_add(K2), 9);
+    round(&g, &mut b, c, d, a, input[7].wrapping_add(K2), 13);
+    round(&g, &mut a, b, c, d, input[0].wrapping_add(K2), 3);
+    round(&g, &mut d, a, b, c, input[2].wrapping_add(K2), 5);
+    round(&g, &mut c, d, a, b, input[4].wrapping_add(K2), 9);
+    round(&g, &mut b, c, d, a, input[6].wrapping_add(K2), 13);
+
+    round(&h, &mut a, b, c, d, input[3].wrapping_add(K3), 3);
+    round(&h, &mut d, a, b, c, input[7].wrapping_add(K3), 9);
+    round(&h, &mut c, d, a, b, input[2].wrapping_add(K3), 11);
+    round(&h, &mut b, c, d, a, input[6].wrapping_add(K3), 15);
+    round(&h, &mut a, b, c, d, input[1].wrapping_add(K3), 3);
+    round(&h, &mut d, a, b, c, input[5].wrapping_add(K3), 9);
+    round(&h, &mut c, d, a, b, input[0].wrapping_add(K3), 11);
+    round(&h, &mut b, c, d, a, input[4].wrapping_add(K3), 15);
+
+    buf[0] = buf[0].wrapping_add(a);
+    buf[1] = buf[1].wrapping_add(b);
+    buf[2] = buf[2].wrapping_add(c);
+    buf[3] = buf[3].wrapping_add(d);
+}
+
+fn tea_transform(buf: &mut [u32; 4], input: &[u32; 8]) {
+    const DELTA: u32 = 0x9e37_79b9;
+
+    let (mut sum, mut b0, mut b1) = (0u32, buf[0], buf[1]);
+    let [a, b, c, d] = [input[0], input[1], input[2], input[3]];
+    for _ in 0..16 {
+        sum = sum.wrapping_add(DELTA);
+        b0 = b0.wrapping_add(
+            ((b1 << 4).wrapping_add(a)) ^ (b1.wrapping_add(sum)) ^ ((b1 >> 5).wrapping_add(b)),
+        );
+        b1 = b1.wrapping_add(
+            ((b0 << 4).wrapping_add(c)) ^ (b0.wrapping_add(sum)) ^ ((b0 >> 5).wrapping_add(d)),
+        );
+    }
+    buf[0] = buf[0].wrapping_add(b0);
+    buf[1] = buf[1].wrapping_add(b1);
+}
@@ -14,6 +14,7 @@ impl FileSystem for Ext2 {
     fn sync(&self) -> Result<()> {
         self.sync_all_inodes()?;
         self.sync_metadata()?;
+        self.commit_journal()?;
 
         self.block_device().sync()?;
         Ok(())
@@ -10,8 +10,9 @@ use crate::{
     fs::{
         ext2::{FilePerm, Inode as Ext2Inode},
         utils::{
-            DirentVisitor, Extension, FallocMode, FileSystem, Inode, InodeMode, InodeType,
-            IoctlCmd, Metadata, MknodType, XattrName, XattrNamespace, XattrSetFlags,
+            DirentVisitor, Extension, FallocMode, FileSystem, FsVerity, FsVerityParams, Inode,
+            InodeMode, InodeType, IoctlCmd, Metadata, MknodType, XattrName, XattrNamespace,
+            XattrSetFlags,
         },
     },
     prelude::*,
@@ -176,12 +177,22 @@ impl Inode for Ext2Inode {
     }
 
     fn sync_all(&self) -> Result<()> {
+        // The metadata in the running transaction reaches the device only on commits.
+        if self.fs().has_journal() {
+            return FileSystem::sync(self.fs().as_ref());
+        }
+
         self.sync_all()?;
         self.fs().block_device().sync()?;
         Ok(())
     }
 
     fn sync_data(&self) -> Result<()> {
+        // The metadata in the running transaction reaches the device only on commits.
+        if self.fs().has_journal() {
+            return FileSystem::sync(self.fs().as_ref());
+        }
+
         self.sync_data()?;
         self.fs().block_device().sync()?;
         Ok(())
@@ -215,6 +226,14 @@ impl Inode for Ext2Inode {
     fn remove_xattr(&self, name: XattrName) -> Result<()> {
         self.remove_xattr(name)
     }
+
+    fn verity(&self) -> Result<Option<Arc<FsVerity>>> {
+        self.verity()
+    }
+
+    fn enable_verity(&self, params: &FsVerityParams) -> Result<()> {
+        self.enable_verity(params)
+    }
 }
 
 impl From<FilePerm> for InodeMode {
@@ -8,11 +8,18 @@ use core::sync::atomic::{AtomicUsize, Ordering};
 
 use inherit_methods_macro::inherit_methods;
 use ostd::{const_assert, mm::UntypedMem};
+use spin::Once;
 
 use super::{
     block_ptr::{BidPath, BlockPtrs, Ext2Bid, BID_SIZE, MAX_BLOCK_PTRS},
-    dir::{DirEntryHeader, DirEntryItem, DirEntryReader, DirEntryWriter},
+    csum::crc32c,
+    dir::{
+        set_dir_block_checksum, DirEntryHeader, DirEntryItem, DirEntryReader, DirEntryWriter,
+        DIR_TAIL_LEN,
+    },
+    extent::{BlockMapping, ExtentTree},
     fs::Ext2,
+    htree,
     indirect_block_cache::{IndirectBlock, IndirectBlockCache},
     prelude::*,
     utils::now,
@@ -22,8 +29,8 @@ use crate::{
     fs::{
         path::{is_dot, is_dot_or_dotdot, is_dotdot},
         utils::{
-            Extension, FallocMode, Inode as _, InodeMode, Metadata, Permission, XattrName,
-            XattrNamespace, XattrSetFlags,
+            Extension, FallocMode, FsVerity, FsVerityDescriptor, FsVerityParams, Inode as _,
+            InodeMode, Metadata, Permission, XattrName, XattrNamespace, XattrSetFlags,
         },
     },
     process::{posix_thread::AsPosixThread, Gid, Uid},
@@ -35,6 +42,9 @@ pub const MAX_FNAME_LEN: usize = 255;
 /// Max path length of the fast symlink.
 pub const MAX_FAST_SYMLINK_LEN: usize = MAX_BLOCK_PTRS * BID_SIZE;
 
+/// The name of the xattr that keeps the fs-verity descriptor.
+const VERITY_XATTR_NAME: &str = "trusted.fsverity";
+
 /// The Ext2 inode.
 pub struct Inode {
     ino: u32,
@@ -52,18 +62,35 @@ impl Inode {
         block_group_idx: usize,
         desc: Dirty<InodeDesc>,
         fs: Weak<Ext2>,
-    ) -> Arc<Self> {
-        Arc::new_cyclic(|weak_self| Self {
+    ) -> Result<Arc<Self>> {
+        let csum_seed = fs.upgrade().unwrap().inode_csum_seed(ino, desc.generation);
+        let extent_tree = if desc.flags.contains(FileFlags::EXTENTS) {
+            let fs = fs.upgrade().unwrap();
+            let extent_tree = ExtentTree::load(&desc.block_ptrs, csum_seed, &mut |bid, buf| {
+                fs.read_metadata_bytes(Bid::new(bid as u64).to_offset(), buf)
+            })?;
+            Some(extent_tree)
+        } else {
+            None
+        };
+
+        Ok(Arc::new_cyclic(|weak_self| Self {
             ino,
             type_: desc.type_,
             block_group_idx,
             xattr: desc
                 .acl
                 .map(|acl| Xattr::new(acl, weak_self.clone(), fs.clone())),
-            inner: RwMutex::new(InodeInner::new(desc, weak_self.clone(), fs.clone())),
+            inner: RwMutex::new(InodeInner::new(
+                desc,
+                extent_tree,
+                csum_seed,
+                weak_self.clone(),
+                fs.clone(),
+            )),
             fs,
             extension: Extension::new(),
-        })
+        }))
     }
 
     pub fn ino(&self) -> u32 {
@@ -97,6 +124,7 @@ impl Inode {
             atime: inner.atime(),
             mtime: inner.mtime(),
             ctime: inner.ctime(),
+            btime: None,
             type_: self.type_,
             mode: InodeMode::from(inner.file_perm()),
             nlinks: inner.hard_links() as _,
@@ -110,6 +138,7 @@ impl Inode {
         if self.type_ != InodeType::File {
             return_errno!(Errno::EISDIR);
         }
+        self.check_data_mutable()?;
 
         let inner = self.inner.upread();
         if new_size == inner.file_size() {
@@ -615,14 +644,14 @@ impl Inode {
 
             let try_readdir = |offset: &mut usize, visitor: &mut dyn DirentVisitor| -> Result<()> {
                 let mut dir_entry_reader = DirEntryReader::new(&inner.page_cache, *offset);
-                for dir_entry in dir_entry_reader.iter_entries() {
+                for (entry_offset, dir_entry) in dir_entry_reader.iter_entries() {
                     visitor.visit(
                         dir_entry.name(),
                         dir_entry.ino() as u64,
                         dir_entry.type_(),
                         dir_entry.record_len(),
                     )?;
-                    *offset += dir_entry.record_len();
+                    *offset = entry_offset + dir_entry.record_len();
                 }
 
                 Ok(())
@@ -681,6 +710,8 @@ impl Inode {
         if self.type_ != InodeType::File {
             return_errno!(Errno::EISDIR);
         }
+        // Load the fs-verity state before reading, so that the data is verified.
+        self.verity()?;
 
         let bytes_read = self.inner.read().read_at(offset, writer)?;
 
@@ -697,6 +728,11 @@ impl Inode {
         if !is_block_aligned(offset) || !is_block_aligned(writer.avail()) {
             return_errno_with_message!(Errno::EINVAL, "not block-aligned");
         }
+        // The data read from the device directly cannot be verified, so the verity files are
+        // read through the page cache.
+        if self.file_flags().contains(FileFlags::VERITY) {
+            return self.read_at(offset, writer);
+        }
 
         let bytes_read = self.inner.read().read_direct_at(offset, writer)?;
 
@@ -709,6 +745,7 @@ impl Inode {
         if self.type_ != InodeType::File {
             return_errno!(Errno::EISDIR);
         }
+        self.check_data_mutable()?;
 
         let inner = self.inner.upread();
         let file_size = inner.file_size();
@@ -738,6 +775,7 @@ impl Inode {
         if !is_block_aligned(offset) || !is_block_aligned(reader.remain()) {
             return_errno_with_message!(Errno::EINVAL, "not block aligned");
         }
+        self.check_data_mutable()?;
 
         let mut inner = self.inner.write();
         let bytes_written = inner.write_direct_at(offset, reader)?;
@@ -785,6 +823,7 @@ impl Inode {
         if self.type_ != InodeType::File {
             return_errno_with_message!(Errno::EISDIR, "not regular file");
         }
+        self.check_data_mutable()?;
 
         match mode {
             FallocMode::PunchHoleKeepSize => {
@@ -861,6 +900,92 @@ impl Inode {
         self.check_permission(Permission::MAY_WRITE)?;
         self.xattr.as_ref().unwrap().remove(name)
     }
+
+    /// Returns the fs-verity state of the file, which is loaded on the first call.
+    ///
+    /// Unlike ext4 in Linux, which stores the Merkle tree after the end of the file, only the
+    /// descriptor is persisted in an xattr. The Merkle tree is rebuilt over the data when the
+    /// state is loaded, which also checks the root hash.
+    pub fn verity(&self) -> Result<Option<Arc<FsVerity>>> {
+        if !self.file_flags().contains(FileFlags::VERITY) {
+            return Ok(None);
+        }
+        if let Some(verity) = self.inner.read().verity() {
+            return Ok(Some(verity.clone()));
+        }
+
+        // The xattr is read without holding the lock, since reading it may update the inode.
+        let mut descriptor = FsVerityDescriptor::new_zeroed();
+        let descriptor_len = self
+            .xattr
+            .as_ref()
+            .unwrap()
+            .get(
+                XattrName::try_from_full_name(VERITY_XATTR_NAME).unwrap(),
+                &mut VmWriter::from(descriptor.as_bytes_mut()).to_fallible(),
+            )
+            .map_err(|_| Error::with_message(Errno::EINVAL, "the fs-verity descriptor is lost"))?;
+        if descriptor_len != core::mem::size_of::<FsVerityDescriptor>() {
+            return_errno_with_message!(Errno::EINVAL, "the fs-verity descriptor is invalid");
+        }
+
+        let inner = self.inner.upread();
+        if let Some(verity) = inner.verity() {
+            return Ok(Some(verity.clone()));
+        }
+        let verity = FsVerity::load(descriptor, inner.file_size(), &mut |idx, block| {
+            inner.read_at(idx * BLOCK_SIZE, &mut VmWriter::from(block).to_fallible())?;
+            Ok(())
+        })?;
+        if verity.is_corrupted() {
+            // The pages read to build the Merkle tree have not been verified.
+            let pages = inner.page_cache.pages();
+            pages.decommit(0..pages.size())?;
+        }
+
+        let verity = Arc::new(verity);
+        inner.set_verity(verity.clone());
+        Ok(Some(verity))
+    }
+
+    /// Enables fs-verity on the file.
+    pub fn enable_verity(&self, params: &FsVerityParams) -> Result<()> {
+        if self.type_ != InodeType::File {
+            return_errno_with_message!(Errno::EINVAL, "not regular file");
+        }
+
+        let verity = {
+            let inner = self.inner.read();
+            if inner.file_flags().contains(FileFlags::VERITY) {
+                return_errno_with_message!(Errno::EEXIST, "fs-verity is already enabled");
+            }
+            FsVerity::build(params, inner.file_size(), &mut |idx, block| {
+                inner.read_at(idx * BLOCK_SIZE, &mut VmWriter::from(block).to_fallible())?;
+                Ok(())
+            })?
+        };
+
+        // The xattr is written without holding the lock, since writing it may update the inode.
+        self.xattr.as_ref().unwrap().set(
+            XattrName::try_from_full_name(VERITY_XATTR_NAME).unwrap(),
+            &mut VmReader::from(verity.descriptor().as_bytes()).to_fallible(),
+            XattrSetFlags::CREATE_OR_REPLACE,
+        )?;
+
+        let mut inner = self.inner.write();
+        inner.set_verity(Arc::new(verity));
+        inner.insert_file_flags(FileFlags::VERITY);
+        inner.set_ctime(now());
+        Ok(())
+    }
+
+    /// Checks whether the data of the file can be changed, which is not true for verity files.
+    fn check_data_mutable(&self) -> Result<()> {
+        if self.file_flags().contains(FileFlags::VERITY) {
+            return_errno_with_message!(Errno::EPERM, "the file is protected by fs-verity");
+        }
+        Ok(())
+    }
 }
 
 #[inherit_methods(from = "self.inner.read()")]
@@ -956,9 +1081,15 @@ struct InodeInner {
 }
 
 impl InodeInner {
-    pub fn new(desc: Dirty<InodeDesc>, weak_self: Weak<Inode>, fs: Weak<Ext2>) -> Self {
+    pub fn new(
+        desc: Dirty<InodeDesc>,
+        extent_tree: Option<ExtentTree>,
+        csum_seed: Option<u32>,
+        weak_self: Weak<Inode>,
+        fs: Weak<Ext2>,
+    ) -> Self {
         let num_page_bytes = desc.num_page_bytes();
-        let inode_impl = InodeImpl::new(desc, weak_self, fs);
+        let inode_impl = InodeImpl::new(desc, extent_tree, csum_seed, weak_self, fs);
         Self {
             page_cache: PageCache::with_capacity(
                 num_page_bytes,
@@ -1077,16 +1208,61 @@ impl InodeInner {
 
     fn init_dir(&mut self, self_ino: u32, parent_ino: u32) -> Result<()> {
         debug_assert_eq!(self.inode_type(), InodeType::Dir);
-        DirEntryWriter::new(&self.page_cache, 0).init_dir(self_ino, parent_ino)?;
+        DirEntryWriter::new(&self.page_cache, 0, self.dir_tail_len())
+            .init_dir(self_ino, parent_ino)?;
         self.inc_hard_links(); // for ".."
         Ok(())
     }
 
+    /// Returns the length of the tail at the end of each directory block.
+    fn dir_tail_len(&self) -> usize {
+        if self.inode_impl.fs().csum_seed().is_some() {
+            DIR_TAIL_LEN
+        } else {
+            0
+        }
+    }
+
+    /// Drops the hash tree index of the directory before it is modified.
+    fn remove_dir_index(&mut self) -> Result<()> {
+        if !self.file_flags().contains(FileFlags::INDEX_DIR) {
+            return Ok(());
+        }
+
+        let nblocks = self.page_cache.pages().size() / BLOCK_SIZE;
+        htree::remove_index(&self.page_cache, nblocks, self.dir_tail_len())?;
+        self.inode_impl.remove_file_flags(FileFlags::INDEX_DIR);
+        Ok(())
+    }
+
     pub fn contains_entry(&self, name: &str) -> bool {
-        DirEntryReader::new(&self.page_cache, 0).contains_entry(name)
+        self.find_entry_item(name).is_some()
+    }
+
+    pub fn verity(&self) -> Option<&Arc<FsVerity>> {
+        self.inode_impl.block_manager.verity.get()
+    }
+
+    pub fn set_verity(&self, verity: Arc<FsVerity>) {
+        self.inode_impl.block_manager.verity.call_once(|| verity);
     }
 
     pub fn find_entry_item(&self, name: &str) -> Option<DirEntryItem> {
+        // The "." and ".." entries are in the root block of the index, which is not a leaf.
+        if self.file_flags().contains(FileFlags::INDEX_DIR) && !is_dot_or_dotdot(name) {
+            if let Some(hash_params) = self.inode_impl.fs().hash_params() {
+                let nblocks = self.page_cache.pages().size() / BLOCK_SIZE;
+                match htree::lookup(&self.page_cache, nblocks, name, &hash_params) {
+                    Ok(leaf_blocks) => {
+                        return DirEntryReader::new(&self.page_cache, 0)
+                            .find_entry_item_in_blocks(name, &leaf_blocks);
+                    }
+                    Err(e) => {
+                        debug!("ext4: falls back to linear lookup: {:?}", e);
+                    }
+                }
+            }
+        }
         DirEntryReader::new(&self.page_cache, 0).find_entry_item(name)
     }
 
@@ -1101,8 +1277,9 @@ impl InodeInner {
         name: &str,
         check_existence: bool,
     ) -> Result<()> {
+        self.remove_dir_index()?;
         let entry_header = DirEntryHeader::new(ino, inode_type, name.len());
-        DirEntryWriter::new(&self.page_cache, 0).append_new_entry(
+        DirEntryWriter::new(&self.page_cache, 0, self.dir_tail_len()).append_new_entry(
             entry_header,
             name,
             check_existence,
@@ -1123,7 +1300,9 @@ impl InodeInner {
     }
 
     pub fn remove_entry_at(&mut self, name: &str, offset: usize) -> Result<()> {
-        let removed_entry = DirEntryWriter::new(&self.page_cache, offset).remove_entry(name)?;
+        self.remove_dir_index()?;
+        let removed_entry = DirEntryWriter::new(&self.page_cache, offset, self.dir_tail_len())
+            .remove_entry(name)?;
         let file_size = self.file_size();
         let page_cache_size = self.page_cache.pages().size();
         if page_cache_size < file_size {
@@ -1136,7 +1315,9 @@ impl InodeInner {
     }
 
     pub fn rename_entry_at(&mut self, old_name: &str, new_name: &str, offset: usize) -> Result<()> {
-        DirEntryWriter::new(&self.page_cache, offset).rename_entry(old_name, new_name)?;
+        self.remove_dir_index()?;
+        DirEntryWriter::new(&self.page_cache, offset, self.dir_tail_len())
+            .rename_entry(old_name, new_name)?;
         let file_size = self.file_size();
         let page_cache_size = self.page_cache.pages().size();
         if page_cache_size != file_size {
@@ -1146,9 +1327,11 @@ impl InodeInner {
     }
 
     pub fn set_parent_ino(&mut self, parent_ino: u32) -> Result<()> {
+        // The ".." entry is covered by the checksum of the index root.
+        self.remove_dir_index()?;
         let mut entry_item = self.find_entry_item("..").unwrap();
         entry_item.set_ino(parent_ino);
-        DirEntryWriter::new(&self.page_cache, entry_item.offset())
+        DirEntryWriter::new(&self.page_cache, entry_item.offset(), self.dir_tail_len())
             .write_header_only(entry_item.header())?;
         Ok(())
     }
@@ -1172,6 +1355,7 @@ impl InodeInner {
     pub fn gid(&self) -> u32;
     pub fn set_gid(&mut self, gid: u32);
     pub fn file_flags(&self) -> FileFlags;
+    pub fn insert_file_flags(&mut self, flags: FileFlags);
     pub fn hard_links(&self) -> u16;
     pub fn inc_hard_links(&mut self);
     pub fn dec_hard_links(&mut self);
@@ -1198,11 +1382,21 @@ struct InodeImpl {
 }
 
 impl InodeImpl {
-    pub fn new(desc: Dirty<InodeDesc>, weak_self: Weak<Inode>, fs: Weak<Ext2>) -> Self {
+    pub fn new(
+        desc: Dirty<InodeDesc>,
+        extent_tree: Option<ExtentTree>,
+        csum_seed: Option<u32>,
+        weak_self: Weak<Inode>,
+        fs: Weak<Ext2>,
+    ) -> Self {
         let block_manager = InodeBlockManager {
             nblocks: AtomicUsize::new(desc.blocks_count() as _),
             block_ptrs: RwMutex::new(desc.block_ptrs),
             indirect_blocks: RwMutex::new(IndirectBlockCache::new(fs.clone())),
+            extent_tree: extent_tree.map(RwMutex::new),
+            csum_seed,
+            is_dir: desc.type_ == InodeType::Dir,
+            verity: Once::new(),
             fs,
         };
         Self {
@@ -1258,6 +1452,14 @@ impl InodeImpl {
         self.desc.flags
     }
 
+    pub fn insert_file_flags(&mut self, flags: FileFlags) {
+        self.desc.flags.insert(flags);
+    }
+
+    pub fn remove_file_flags(&mut self, flags: FileFlags) {
+        self.desc.flags.remove(flags);
+    }
+
     pub fn hard_links(&self) -> u16 {
         self.desc.hard_links
     }
@@ -1339,7 +1541,12 @@ impl InodeImpl {
     }
 
     pub fn sync_metadata(&mut self) -> Result<()> {
-        if !self.desc.is_dirty() {
+        let is_extent_tree_dirty = self
+            .block_manager
+            .extent_tree
+            .as_ref()
+            .is_some_and(|extent_tree| extent_tree.read().is_dirty());
+        if !self.desc.is_dirty() && !is_extent_tree_dirty {
             return Ok(());
         }
 
@@ -1359,10 +1566,52 @@ impl InodeImpl {
         }
 
         self.block_manager.indirect_blocks.write().evict_all()?;
+        self.persist_extent_tree()?;
         inode.fs().sync_inode(inode.ino(), &self.desc)?;
         self.desc.clear_dirty();
         Ok(())
     }
+
+    /// Writes back the extent tree, and updates the root and the block count in the descriptor.
+    fn persist_extent_tree(&mut self) -> Result<()> {
+        let block_manager = self.block_manager.clone();
+        let Some(extent_tree) = block_manager.extent_tree.as_ref() else {
+            return Ok(());
+        };
+
+        let fs = self.fs();
+        let mut extent_tree = extent_tree.write();
+        if extent_tree.is_dirty() {
+            let block_group_idx = self.inode().block_group_idx;
+            let root = extent_tree.persist(
+                &mut || {
+                    fs.alloc_blocks(block_group_idx, 1)
+                        .map(|range| range.start)
+                        .ok_or_else(|| Error::with_message(Errno::ENOSPC, "no space for extents"))
+                },
+                &mut |bid| fs.free_blocks(bid..bid + 1),
+                &mut |bid, node| {
+                    fs.write_metadata_bytes_async(Bid::new(bid as u64).to_offset(), node)?
+                        .wait()
+                        .ok_or_else(|| {
+                            Error::with_message(Errno::EIO, "failed to write extents")
+                        })?;
+                    Ok(())
+                },
+            )?;
+            self.desc.block_ptrs = root;
+            *block_manager.block_ptrs.write() = root;
+        }
+
+        // The number of blocks is in the unit of 512-byte sectors, including the xattr block.
+        let xattr_blocks = match self.desc.acl {
+            Some(acl) if acl.to_raw() != 0 => 1,
+            _ => 0,
+        };
+        let sectors = (extent_tree.allocated_blocks() + xattr_blocks) * (BLOCK_SIZE / 512) as u64;
+        self.desc.blocks_count = sectors as u32;
+        Ok(())
+    }
 }
 
 // Heavy implementation for inode resizing.
@@ -1390,7 +1639,11 @@ impl InodeImpl {
             if new_blocks - old_blocks > self.fs().super_block().free_blocks_count() {
                 return_errno_with_message!(Errno::ENOSPC, "not enough free blocks");
             }
-            self.expand_blocks(old_blocks..new_blocks)?;
+            if self.block_manager.extent_tree.is_some() {
+                self.expand_extent_blocks(old_blocks..new_blocks)?;
+            } else {
+                self.expand_blocks(old_blocks..new_blocks)?;
+            }
         }
 
         // Expands the size
@@ -1414,6 +1667,38 @@ impl InodeImpl {
         Ok(())
     }
 
+    /// Expands the blocks of an inode with extents.
+    ///
+    /// The blocks in `range` that are already mapped, e.g., the preallocated ones,
+    /// are kept.
+    fn expand_extent_blocks(&mut self, range: Range<Ext2Bid>) -> Result<()> {
+        let fs = self.fs();
+        let block_manager = self.block_manager.clone();
+        let mut extent_tree = block_manager.extent_tree.as_ref().unwrap().write();
+        let block_group_idx = extent_tree
+            .goal()
+            .map_or(self.inode().block_group_idx, |bid| {
+                (bid / fs.blocks_per_group()) as usize
+            });
+
+        for mut hole in extent_tree.holes(range.clone()) {
+            while !hole.is_empty() {
+                let Some(device_range) = fs.alloc_blocks(block_group_idx, hole.len() as Ext2Bid)
+                else {
+                    for freed_range in extent_tree.truncate(range.start) {
+                        fs.free_blocks(freed_range)?;
+                    }
+                    return_errno_with_message!(Errno::ENOSPC, "can not allocate blocks");
+                };
+                let len = device_range.len() as Ext2Bid;
+                extent_tree.insert(hole.start, device_range);
+                hole.start += len;
+            }
+        }
+
+        Ok(())
+    }
+
     /// Attempts to expand a range of blocks and returns the number of consecutive
     /// blocks successfully allocated.
     ///
@@ -1643,7 +1928,13 @@ impl InodeImpl {
         let old_blocks = self.desc.blocks_count();
 
         // Shrinks block count if necessary
-        if new_blocks < old_blocks {
+        if let Some(extent_tree) = self.block_manager.extent_tree.as_ref() {
+            // The blocks preallocated beyond the end are freed as well.
+            let fs = self.fs();
+            for freed_range in extent_tree.write().truncate(new_blocks) {
+                fs.free_blocks(freed_range).unwrap();
+            }
+        } else if new_blocks < old_blocks {
             self.shrink_blocks(new_blocks..old_blocks);
         }
 
@@ -1832,6 +2123,14 @@ struct InodeBlockManager {
     /// frequent reads access the `InodeDesc` copy without locking.
     block_ptrs: RwMutex<BlockPtrs>,
     indirect_blocks: RwMutex<IndirectBlockCache>,
+    /// The extent tree, if the blocks are mapped by extents instead of block pointers.
+    extent_tree: Option<RwMutex<ExtentTree>>,
+    /// The checksum seed of the inode, if the metadata checksums are enabled.
+    csum_seed: Option<u32>,
+    /// Whether the blocks are the entries of a directory, which are metadata.
+    is_dir: bool,
+    /// The fs-verity state, against which the blocks read to the page cache are verified.
+    verity: Once<Arc<FsVerity>>,
     fs: Weak<Ext2>,
 }
 
@@ -1846,6 +2145,28 @@ impl InodeBlockManager {
         debug_assert!(nblocks * BLOCK_SIZE <= writer.avail());
         let mut bio_waiter = BioWaiter::new();
 
+        if let Some(extent_tree) = self.extent_tree.as_ref() {
+            let mappings = extent_tree.read().lookup(bid..bid + nblocks as Ext2Bid);
+            for mapping in mappings {
+                match mapping {
+                    BlockMapping::Mapped(dev_range) => {
+                        let bio_segment =
+                            BioSegment::alloc(dev_range.len(), BioDirection::FromDevice);
+                        bio_segment.reader().unwrap().read_fallible(writer)?;
+
+                        let waiter = self.fs().read_blocks_async(dev_range.start, bio_segment)?;
+                        bio_waiter.concat(waiter);
+                    }
+                    BlockMapping::Zeroed(len) => {
+                        writer
+                            .fill_zeros(len as usize * BLOCK_SIZE)
+                            .map_err(|(err, _)| Error::from(err))?;
+                    }
+                }
+            }
+            return Ok(bio_waiter);
+        }
+
         for dev_range in DeviceRangeReader::new(self, bid..bid + nblocks as Ext2Bid)? {
             let start_bid = dev_range.start as Ext2Bid;
             let range_nblocks = dev_range.len();
@@ -1870,6 +2191,24 @@ impl InodeBlockManager {
     pub fn read_block_async(&self, bid: Ext2Bid, frame: &CachePage) -> Result<BioWaiter> {
         let mut bio_waiter = BioWaiter::new();
 
+        if let Some(extent_tree) = self.extent_tree.as_ref() {
+            let mapping = extent_tree.read().lookup(bid..bid + 1).pop().unwrap();
+            match mapping {
+                BlockMapping::Mapped(dev_range) => {
+                    let bio_segment = BioSegment::new_from_segment(
+                        Segment::from(frame.clone()).into(),
+                        BioDirection::FromDevice,
+                    );
+                    let waiter = self.fs().read_blocks_async(dev_range.start, bio_segment)?;
+                    bio_waiter.concat(waiter);
+                }
+                BlockMapping::Zeroed(_) => {
+                    frame.writer().fill(0u8);
+                }
+            }
+            return Ok(bio_waiter);
+        }
+
         for dev_range in DeviceRangeReader::new(self, bid..bid + 1 as Ext2Bid)? {
             let start_bid = dev_range.start as Ext2Bid;
             // TODO: Should we allocate the bio segment from the pool on reads?
@@ -1895,7 +2234,7 @@ impl InodeBlockManager {
         debug_assert_eq!(nblocks * BLOCK_SIZE, reader.remain());
         let mut bio_waiter = BioWaiter::new();
 
-        for dev_range in DeviceRangeReader::new(self, bid..bid + nblocks as Ext2Bid)? {
+        for dev_range in self.device_ranges_for_write(bid..bid + nblocks as Ext2Bid)? {
             let start_bid = dev_range.start as Ext2Bid;
             let range_nblocks = dev_range.len();
 
@@ -1919,21 +2258,68 @@ impl InodeBlockManager {
     pub fn write_block_async(&self, bid: Ext2Bid, frame: &CachePage) -> Result<BioWaiter> {
         let mut bio_waiter = BioWaiter::new();
 
-        for dev_range in DeviceRangeReader::new(self, bid..bid + 1 as Ext2Bid)? {
+        for dev_range in self.device_ranges_for_write(bid..bid + 1 as Ext2Bid)? {
             let start_bid = dev_range.start as Ext2Bid;
             let bio_segment = BioSegment::alloc(1, BioDirection::ToDevice);
-            // This requires an additional copy to the pooled bio segment.
-            bio_segment
-                .writer()
-                .unwrap()
-                .write_fallible(&mut frame.reader().to_fallible())?;
-            let waiter = self.fs().write_blocks_async(start_bid, bio_segment)?;
+            let waiter = if self.is_dir {
+                let mut block = vec![0u8; BLOCK_SIZE];
+                frame.read_bytes(0, &mut block)?;
+                if let Some(csum_seed) = self.csum_seed {
+                    set_dir_block_checksum(&mut block, csum_seed);
+                }
+                bio_segment.write_bytes(0, &block)?;
+                self.fs()
+                    .write_metadata_blocks_async(start_bid, bio_segment)?
+            } else {
+                // This requires an additional copy to the pooled bio segment.
+                bio_segment
+                    .writer()
+                    .unwrap()
+                    .write_fallible(&mut frame.reader().to_fallible())?;
+                self.fs().write_blocks_async(start_bid, bio_segment)?
+            };
             bio_waiter.concat(waiter);
         }
 
         Ok(bio_waiter)
     }
 
+    /// Returns the device ranges to write the blocks in `range`.
+    ///
+    /// For an inode with extents, the unwritten blocks in `range` are marked
+    /// as written.
+    fn device_ranges_for_write(&self, range: Range<Ext2Bid>) -> Result<Vec<Range<Ext2Bid>>> {
+        let Some(extent_tree) = self.extent_tree.as_ref() else {
+            return Ok(DeviceRangeReader::new(self, range)?.collect());
+        };
+
+        let mut extent_tree = extent_tree.write();
+        let mut device_ranges: Vec<Range<Ext2Bid>> = Vec::new();
+        let mut push_range = |device_range: Range<Ext2Bid>| match device_ranges.last_mut() {
+            Some(last) if last.end == device_range.start => last.end = device_range.end,
+            _ => device_ranges.push(device_range),
+        };
+        let mut current = range.start;
+        for mapping in extent_tree.lookup(range.clone()) {
+            match mapping {
+                BlockMapping::Mapped(device_range) => {
+                    current += device_range.len() as Ext2Bid;
+                    push_range(device_range);
+                }
+                BlockMapping::Zeroed(len) => {
+                    for block in current..current + len {
+                        let Some(device_bid) = extent_tree.mark_written(block) else {
+                            return_errno_with_message!(Errno::EIO, "write to unmapped blocks");
+                        };
+                        push_range(device_bid..device_bid + 1);
+                    }
+                    current += len;
+                }
+            }
+        }
+        Ok(device_ranges)
+    }
+
     pub fn nblocks(&self) -> usize {
         self.nblocks.load(Ordering::Acquire)
     }
@@ -1954,9 +2340,29 @@ impl PageCacheBackend for InodeBlockManager {
         self.write_block_async(bid, frame)
     }
 
+    fn verify_page(&self, idx: usize, frame: &CachePage) -> Result<()> {
+        // The pages read before the fs-verity state is loaded are checked by the loading, which
+        // builds the Merkle tree from them.
+        let Some(verity) = self.verity.get() else {
+            return Ok(());
+        };
+
+        let mut block = vec![0u8; BLOCK_SIZE];
+        frame.read_bytes(0, &mut block)?;
+        verity.verify_block(idx, &block)
+    }
+
     fn npages(&self) -> usize {
         self.nblocks()
     }
+
+    fn plug(&self) {
+        self.fs().block_device().plug();
+    }
+
+    fn unplug(&self) {
+        self.fs().block_device().unplug();
+    }
 }
 
 /// A reader to get the corresponding device block IDs for a specified range.
@@ -2123,6 +2529,10 @@ pub(super) struct InodeDesc {
     block_ptrs: BlockPtrs,
     /// File or directory acl block.
     acl: Option<Bid>,
+    /// File version (for NFS).
+    generation: u32,
+    /// Inode version, which is OS dependent value 1.
+    version: u32,
 }
 
 impl TryFrom<RawInode> for InodeDesc {
@@ -2150,10 +2560,11 @@ impl TryFrom<RawInode> for InodeDesc {
                 .ok_or(Error::with_message(Errno::EINVAL, "invalid file flags"))?,
             block_ptrs: inode.block_ptrs,
             acl: match inode_type {
-                InodeType::File => Some(Bid::new(inode.file_acl as _)),
-                InodeType::Dir => Some(Bid::new(inode.size_high as _)),
+                InodeType::File | InodeType::Dir => Some(Bid::new(inode.file_acl as _)),
                 _ => None,
             },
+            generation: inode.generation,
+            version: inode.version,
         })
     }
 }
@@ -2180,9 +2591,18 @@ impl InodeDesc {
                 InodeType::File | InodeType::Dir => Some(Bid::new(0)),
                 _ => None,
             },
+            generation: 0,
+            version: 0,
         })
     }
 
+    /// Maps the blocks of the new inode by extents instead of block pointers.
+    pub fn enable_extents(&mut self) {
+        debug_assert_eq!(self.size, 0);
+        self.flags.insert(FileFlags::EXTENTS);
+        self.block_ptrs = ExtentTree::empty_root();
+    }
+
     pub fn num_page_bytes(&self) -> usize {
         (self.blocks_count() as usize) * BLOCK_SIZE
     }
@@ -2190,9 +2610,10 @@ impl InodeDesc {
     /// Returns the actual number of blocks utilized.
     ///
     /// Ext2 allows the `block_count` to exceed the actual number of blocks utilized.
+    /// The files with extents may have holes, so their `block_count` may be smaller.
     pub fn blocks_count(&self) -> Ext2Bid {
         let blocks = self.size_to_blocks(self.size);
-        assert!(blocks <= self.blocks_count);
+        assert!(self.flags.contains(FileFlags::EXTENTS) || blocks <= self.blocks_count);
         blocks
     }
 
@@ -2280,6 +2701,30 @@ bitflags! {
         const DIR_SYNC = 1 << 16;
         /// Top of directory hierarchies.
         const TOP_DIR = 1 << 17;
+        /// Set to each huge file.
+        const HUGE_FILE = 1 << 18;
+        /// Inode uses extents.
+        const EXTENTS = 1 << 19;
+        /// Verity protected inode.
+        const VERITY = 1 << 20;
+        /// Inode used for large extended attribute.
+        const EA_INODE = 1 << 21;
+        /// Blocks allocated beyond the end of file.
+        const EOF_BLOCKS = 1 << 22;
+        /// Snapshot file.
+        const SNAPFILE = 1 << 24;
+        /// Direct access.
+        const DAX = 1 << 25;
+        /// Snapshot is being deleted.
+        const SNAPFILE_DELETED = 1 << 26;
+        /// Snapshot shrink has completed.
+        const SNAPFILE_SHRUNK = 1 << 27;
+        /// Inode has inline data.
+        const INLINE_DATA = 1 << 28;
+        /// Create with parents projid.
+        const PROJ_INHERIT = 1 << 29;
+        /// Casefolded directory.
+        const CASEFOLD = 1 << 30;
         /// Reserved for ext2 lib.
         const RESERVED = 1 << 31;
     }
@@ -2311,8 +2756,8 @@ pub(super) struct RawInode {
     pub blocks_count: u32,
     /// File flags.
     pub flags: u32,
-    /// OS dependent Value 1.
-    reserved1: u32,
+    /// OS dependent Value 1, which is the inode version on Linux.
+    pub version: u32,
     /// Pointers to blocks.
     pub block_ptrs: BlockPtrs,
     /// File version (for NFS).
@@ -2345,12 +2790,14 @@ impl From<&InodeDesc> for RawInode {
             blocks_count: inode.blocks_count,
             flags: inode.flags.bits(),
             block_ptrs: inode.block_ptrs,
+            generation: inode.generation,
+            version: inode.version,
             file_acl: match inode.acl {
-                Some(acl) if inode.type_ == InodeType::File => acl.to_raw() as u32,
+                Some(acl) => acl.to_raw() as u32,
                 _ => Default::default(),
             },
-            size_high: match inode.acl {
-                Some(acl) if inode.type_ == InodeType::Dir => acl.to_raw() as u32,
+            size_high: match inode.type_ {
+                InodeType::File => (inode.size >> 32) as u32,
                 _ => Default::default(),
             },
             os_dependent_2: Osd2 {
@@ -2367,16 +2814,60 @@ impl From<&InodeDesc> for RawInode {
 #[repr(C)]
 #[derive(Clone, Copy, Default, Debug, Pod)]
 pub(super) struct Osd2 {
-    /// Fragment number.
-    pub frag_num: u8,
-    /// Fragment size.
-    pub frag_size: u8,
-    pad1: u16,
+    /// High 16 bits of the number of blocks.
+    pub blocks_high: u16,
+    /// High 16 bits of File ACL.
+    pub file_acl_high: u16,
     /// High 16 bits of User Id.
     pub uid_high: u16,
     /// High 16 bits of Group Id.
     pub gid_high: u16,
-    reserved2: u32,
+    /// Low 16 bits of the checksum.
+    pub checksum_lo: u16,
+    reserved2: u16,
+}
+
+/// Updates the checksum of the on-disk inode `raw_inode` numbered `ino`.
+///
+/// The `raw_inode` contains the whole on-disk inode, including the space after `RawInode`,
+/// whose first field is the size of the extra fields.
+pub(super) fn update_raw_inode_checksum(fs_csum_seed: u32, ino: u32, raw_inode: &mut [u8]) {
+    const OLD_INODE_SIZE: usize = core::mem::size_of::<RawInode>();
+    const CHECKSUM_LO_OFFSET: usize = OLD_INODE_SIZE - 4;
+    const CHECKSUM_HI_OFFSET: usize = OLD_INODE_SIZE + 2;
+    const CHECKSUM_HALF_LEN: usize = 2;
+
+    let generation_offset = core::mem::offset_of!(RawInode, generation);
+    let mut csum = crc32c(fs_csum_seed, &ino.to_le_bytes());
+    csum = crc32c(csum, &raw_inode[generation_offset..generation_offset + 4]);
+    csum = crc32c(csum, &raw_inode[..CHECKSUM_LO_OFFSET]);
+    csum = crc32c(csum, &[0u8; CHECKSUM_HALF_LEN]);
+    csum = crc32c(
+        csum,
+        &raw_inode[CHECKSUM_LO_OFFSET + CHECKSUM_HALF_LEN..OLD_INODE_SIZE],
+    );
+
+    let mut has_checksum_hi = false;
+    if raw_inode.len() > OLD_INODE_SIZE {
+        let extra_isize =
+            u16::from_le_bytes([raw_inode[OLD_INODE_SIZE], raw_inode[OLD_INODE_SIZE + 1]]);
+        has_checksum_hi =
+            OLD_INODE_SIZE + extra_isize as usize >= CHECKSUM_HI_OFFSET + CHECKSUM_HALF_LEN;
+        csum = crc32c(csum, &raw_inode[OLD_INODE_SIZE..CHECKSUM_HI_OFFSET]);
+        if has_checksum_hi {
+            csum = crc32c(csum, &[0u8; CHECKSUM_HALF_LEN]);
+            csum = crc32c(csum, &raw_inode[CHECKSUM_HI_OFFSET + CHECKSUM_HALF_LEN..]);
+        } else {
+            csum = crc32c(csum, &raw_inode[CHECKSUM_HI_OFFSET..]);
+        }
+    }
+
+    raw_inode[CHECKSUM_LO_OFFSET..CHECKSUM_LO_OFFSET + CHECKSUM_HALF_LEN]
+        .copy_from_slice(&(csum as u16).to_le_bytes());
+    if has_checksum_hi {
+        raw_inode[CHECKSUM_HI_OFFSET..CHECKSUM_HI_OFFSET + CHECKSUM_HALF_LEN]
+            .copy_from_slice(&((csum >> 16) as u16).to_le_bytes());
+    }
 }
 
 fn is_block_aligned(offset: usize) -> bool {
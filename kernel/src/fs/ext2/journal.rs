@@ -0,0 +1,796 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The journal of Ext3 and Ext4, in the format of JBD2.
+//!
+//! The journal is a circular log stored in the blocks of the journal inode.
+//! A transaction in the log consists of descriptor blocks, each of which tells
+//! the home locations of the logged blocks following it, and ends with a commit
+//! block. All the fields of the journal are big-endian.
+//!
+//! When the filesystem is opened, the committed transactions are replayed to
+//! bring the metadata to a consistent state. Later, the modified metadata blocks
+//! are collected in the running transaction, and are written to their home
+//! locations only after the transaction is committed to the log. Since the data
+//! blocks are written before the commit, this works as the ordered mode.
+//!
+//! Each transaction is checkpointed right after its commit, so the log never
+//! holds more than one transaction.
+
+use super::{block_ptr::Ext2Bid, csum::crc32c, prelude::*, utils::now};
+
+const JBD2_MAGIC: u32 = 0xc03b_3998;
+/// The length of the header of the journal blocks.
+const HEADER_LEN: usize = 12;
+/// The length of the tail holding the checksum of descriptor and revoke blocks.
+const BLOCK_TAIL_LEN: usize = 4;
+const UUID_LEN: usize = 16;
+/// The number of fast commit blocks if the superblock does not tell.
+const DEFAULT_FAST_COMMIT_BLOCKS: u32 = 256;
+/// The checksum type of CRC32C.
+const CHECKSUM_TYPE_CRC32C: u8 = 4;
+
+// The offsets of the fields in the superblock.
+const SB_BLOCK_SIZE: usize = 0x0c;
+const SB_MAX_LEN: usize = 0x10;
+const SB_FIRST: usize = 0x14;
+const SB_SEQUENCE: usize = 0x18;
+const SB_START: usize = 0x1c;
+const SB_FEATURE_INCOMPAT: usize = 0x28;
+const SB_UUID: usize = 0x30;
+const SB_CHECKSUM_TYPE: usize = 0x50;
+const SB_NUM_FC_BLOCKS: usize = 0x54;
+const SB_CHECKSUM: usize = 0xfc;
+const SB_LEN: usize = 1024;
+
+// The offsets of the fields in the commit block.
+const COMMIT_CHECKSUM: usize = 0x10;
+const COMMIT_SEC: usize = 0x30;
+const COMMIT_NSEC: usize = 0x38;
+
+// The offset of the field in the revoke block.
+const REVOKE_COUNT: usize = 0x0c;
+
+/// The type of a journal block.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, TryFromInt)]
+enum BlockType {
+    Descriptor = 1,
+    Commit = 2,
+    SuperBlockV1 = 3,
+    SuperBlockV2 = 4,
+    Revoke = 5,
+}
+
+bitflags! {
+    /// Incompatible feature set of the journal.
+    struct JournalIncompatFeatures: u32 {
+        /// Has revoke blocks
+        const REVOKE = 1 << 0;
+        /// The block numbers are 64-bit
+        const IS_64BIT = 1 << 1;
+        /// The commit blocks are written without waiting for the logged blocks
+        const ASYNC_COMMIT = 1 << 2;
+        /// Version 2 of the checksums
+        const CSUM_V2 = 1 << 3;
+        /// Version 3 of the checksums
+        const CSUM_V3 = 1 << 4;
+        /// Has the area of fast commits
+        const FAST_COMMIT = 1 << 5;
+    }
+}
+
+bitflags! {
+    /// Flags of the tags in descriptor blocks.
+    struct TagFlags: u32 {
+        /// The first four bytes of the logged block are replaced by zeros,
+        /// since they happen to be the magic number
+        const ESCAPE = 1 << 0;
+        /// The UUID is the same as the previous tag, so it is omitted
+        const SAME_UUID = 1 << 1;
+        /// The block is deleted by this transaction
+        const DELETED = 1 << 2;
+        /// The last tag in the descriptor block
+        const LAST_TAG = 1 << 3;
+    }
+}
+
+/// The journal of the filesystem.
+pub(super) struct Journal {
+    block_device: Arc<dyn BlockDevice>,
+    /// The device blocks of the journal, indexed by the journal blocks.
+    blocks: Vec<Ext2Bid>,
+    inner: Mutex<JournalInner>,
+}
+
+struct JournalInner {
+    super_block: JournalSuperBlock,
+    /// The metadata blocks modified in the running transaction,
+    /// indexed by their home locations.
+    running: BTreeMap<Ext2Bid, Vec<u8>>,
+}
+
+impl Debug for Journal {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Journal")
+            .field("nblocks", &self.blocks.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Journal {
+    /// Loads the journal stored in the device `blocks`.
+    pub fn load(block_device: Arc<dyn BlockDevice>, blocks: Vec<Ext2Bid>) -> Result<Self> {
+        let Some(&first_bid) = blocks.first() else {
+            return_errno_with_message!(Errno::EINVAL, "empty journal");
+        };
+        let mut buf = vec![0u8; BLOCK_SIZE];
+        block_device.read_bytes(Bid::new(first_bid as u64).to_offset(), &mut buf)?;
+        let super_block = JournalSuperBlock::parse(buf, blocks.len())?;
+        Ok(Self {
+            block_device,
+            blocks,
+            inner: Mutex::new(JournalInner {
+                super_block,
+                running: BTreeMap::new(),
+            }),
+        })
+    }
+
+    /// Replays the committed transactions in the log, if any.
+    pub fn recover(&self) -> Result<()> {
+        let mut inner = self.inner.lock();
+        let super_block = &mut inner.super_block;
+        if super_block.start == 0 {
+            return Ok(());
+        }
+        if super_block
+            .incompat
+            .contains(JournalIncompatFeatures::FAST_COMMIT)
+        {
+            warn!("ext4: the fast commits in the journal are not replayed");
+        }
+
+        let mut revoked = BTreeMap::new();
+        let end_sequence = self.do_pass(super_block, RecoveryPass::Scan, &mut revoked)?;
+        self.do_pass(
+            super_block,
+            RecoveryPass::Revoke(end_sequence),
+            &mut revoked,
+        )?;
+        self.do_pass(
+            super_block,
+            RecoveryPass::Replay(end_sequence),
+            &mut revoked,
+        )?;
+        debug!(
+            "ext4: replayed the journal from transaction {} to {}",
+            super_block.sequence, end_sequence
+        );
+        self.flush()?;
+
+        super_block.sequence = end_sequence.wrapping_add(1);
+        super_block.start = 0;
+        self.write_super_block(super_block)?;
+        self.flush()
+    }
+
+    /// Goes through the log once, and returns the sequence of the first transaction
+    /// that is not committed.
+    ///
+    /// The transactions are scanned until `end_sequence` if it is known.
+    fn do_pass(
+        &self,
+        super_block: &JournalSuperBlock,
+        pass: RecoveryPass,
+        revoked: &mut BTreeMap<u64, u32>,
+    ) -> Result<u32> {
+        let mut sequence = super_block.sequence;
+        let mut jblock = super_block.start;
+        let mut buf = vec![0u8; BLOCK_SIZE];
+        let mut data = vec![0u8; BLOCK_SIZE];
+
+        loop {
+            if pass.end_sequence() == Some(sequence) {
+                break;
+            }
+
+            self.read_block(jblock, &mut buf)?;
+            if read_be32(&buf, 0) != JBD2_MAGIC || read_be32(&buf, 8) != sequence {
+                break;
+            }
+            jblock = super_block.next_block(jblock);
+
+            match BlockType::try_from(read_be32(&buf, 4)) {
+                Ok(BlockType::Descriptor) => {
+                    if !super_block.verify_block_tail(&buf) {
+                        warn!("ext4: invalid checksum of journal descriptor block");
+                        break;
+                    }
+                    for tag in super_block.parse_tags(&buf) {
+                        let data_jblock = jblock;
+                        jblock = super_block.next_block(jblock);
+                        if !matches!(pass, RecoveryPass::Replay(_)) {
+                            continue;
+                        }
+                        if revoked
+                            .get(&tag.home_bid)
+                            .is_some_and(|&revoke_sequence| !tid_gt(sequence, revoke_sequence))
+                        {
+                            continue;
+                        }
+
+                        self.read_block(data_jblock, &mut data)?;
+                        if !super_block.verify_tag(&tag, sequence, &data) {
+                            warn!("ext4: invalid checksum of journaled block {}", tag.home_bid);
+                            continue;
+                        }
+                        if tag.flags.contains(TagFlags::ESCAPE) {
+                            data[..4].copy_from_slice(&JBD2_MAGIC.to_be_bytes());
+                        }
+                        self.block_device
+                            .write_bytes(Bid::new(tag.home_bid).to_offset(), &data)?;
+                    }
+                }
+                Ok(BlockType::Commit) => {
+                    if !super_block.verify_commit_block(&buf) {
+                        warn!("ext4: invalid checksum of journal commit block");
+                        break;
+                    }
+                    sequence = sequence.wrapping_add(1);
+                }
+                Ok(BlockType::Revoke) => {
+                    if !super_block.verify_block_tail(&buf) {
+                        warn!("ext4: invalid checksum of journal revoke block");
+                        break;
+                    }
+                    if matches!(pass, RecoveryPass::Revoke(_)) {
+                        for home_bid in super_block.parse_revoke_records(&buf) {
+                            let revoke_sequence = revoked.entry(home_bid).or_insert(sequence);
+                            if tid_gt(sequence, *revoke_sequence) {
+                                *revoke_sequence = sequence;
+                            }
+                        }
+                    }
+                }
+                _ => break,
+            }
+        }
+
+        Ok(sequence)
+    }
+
+    /// Returns whether any of the device blocks in `range` is in the running transaction.
+    pub fn contains_any(&self, range: Range<Ext2Bid>) -> bool {
+        self.inner.lock().running.range(range).next().is_some()
+    }
+
+    /// Returns the contents of the device blocks in `range` that are in the running transaction.
+    pub fn logged_blocks(&self, range: Range<Ext2Bid>) -> Vec<(Ext2Bid, Vec<u8>)> {
+        self.inner
+            .lock()
+            .running
+            .range(range)
+            .map(|(bid, block)| (*bid, block.clone()))
+            .collect()
+    }
+
+    /// Writes `bytes` to the device at `offset` in the running transaction.
+    ///
+    /// If the running transaction is full, it is committed first.
+    pub fn write_bytes(&self, offset: usize, bytes: &[u8]) -> Result<()> {
+        let mut inner = self.inner.lock();
+        let mut current = offset;
+        while current < offset + bytes.len() {
+            let bid = (current / BLOCK_SIZE) as Ext2Bid;
+            let offset_in_block = current % BLOCK_SIZE;
+            let len = (BLOCK_SIZE - offset_in_block).min(offset + bytes.len() - current);
+
+            if !inner.running.contains_key(&bid)
+                && inner.running.len() >= inner.super_block.max_transaction_blocks()
+            {
+                self.commit_locked(&mut inner)?;
+            }
+            if !inner.running.contains_key(&bid) {
+                let mut block = vec![0u8; BLOCK_SIZE];
+                if len < BLOCK_SIZE {
+                    self.block_device
+                        .read_bytes(Bid::new(bid as u64).to_offset(), &mut block)?;
+                }
+                inner.running.insert(bid, block);
+            }
+
+            let block = inner.running.get_mut(&bid).unwrap();
+            let bytes_offset = current - offset;
+            block[offset_in_block..offset_in_block + len]
+                .copy_from_slice(&bytes[bytes_offset..bytes_offset + len]);
+            current += len;
+        }
+        Ok(())
+    }
+
+    /// Drops the device blocks in `range` from the running transaction, since they are freed.
+    ///
+    /// Otherwise, the stale metadata may overwrite the blocks after they are reused.
+    pub fn forget(&self, range: Range<Ext2Bid>) {
+        let mut inner = self.inner.lock();
+        let bids: Vec<Ext2Bid> = inner.running.range(range).map(|(bid, _)| *bid).collect();
+        for bid in bids {
+            inner.running.remove(&bid);
+        }
+    }
+
+    /// Commits the running transaction, and writes the blocks to their home locations.
+    pub fn commit(&self) -> Result<()> {
+        let mut inner = self.inner.lock();
+        self.commit_locked(&mut inner)
+    }
+
+    fn commit_locked(&self, inner: &mut JournalInner) -> Result<()> {
+        if inner.running.is_empty() {
+            return Ok(());
+        }
+        let running = core::mem::take(&mut inner.running);
+        let super_block = &mut inner.super_block;
+        let sequence = super_block.sequence;
+        let blocks: Vec<(&Ext2Bid, &Vec<u8>)> = running.iter().collect();
+        let tags_per_descriptor = super_block.tags_per_descriptor();
+
+        let nblocks = blocks.len() + blocks.len().div_ceil(tags_per_descriptor) + 1;
+        if nblocks > super_block.log_len() {
+            warn!("ext4: the transaction is too large for the journal");
+            return self.checkpoint(&blocks);
+        }
+
+        // Marks the log as in use.
+        super_block.start = super_block.first;
+        self.write_super_block(super_block)?;
+
+        let mut bio_waiter = BioWaiter::new();
+        let mut jblock = super_block.first;
+        for chunk in blocks.chunks(tags_per_descriptor) {
+            let mut descriptor = vec![0u8; BLOCK_SIZE];
+            write_header(&mut descriptor, BlockType::Descriptor, sequence);
+            let descriptor_jblock = jblock;
+            jblock = super_block.next_block(jblock);
+
+            let mut offset = HEADER_LEN;
+            for (idx, (home_bid, block)) in chunk.iter().enumerate() {
+                let mut data = (*block).clone();
+                let mut flags = TagFlags::empty();
+                if read_be32(&data, 0) == JBD2_MAGIC {
+                    data[..4].fill(0);
+                    flags |= TagFlags::ESCAPE;
+                }
+                if idx > 0 {
+                    flags |= TagFlags::SAME_UUID;
+                }
+                if idx == chunk.len() - 1 {
+                    flags |= TagFlags::LAST_TAG;
+                }
+
+                let tag = Tag {
+                    home_bid: **home_bid as u64,
+                    flags,
+                    checksum: super_block.tag_checksum(sequence, &data),
+                };
+                offset += super_block.write_tag(&mut descriptor[offset..], &tag);
+                if idx == 0 {
+                    descriptor[offset..offset + UUID_LEN].copy_from_slice(&super_block.uuid());
+                    offset += UUID_LEN;
+                }
+
+                bio_waiter.concat(self.write_block_async(jblock, &data)?);
+                jblock = super_block.next_block(jblock);
+            }
+
+            super_block.set_block_tail(&mut descriptor);
+            bio_waiter.concat(self.write_block_async(descriptor_jblock, &descriptor)?);
+        }
+        wait_bios(&bio_waiter)?;
+        drop(bio_waiter);
+        self.flush()?;
+
+        let mut commit_block = vec![0u8; BLOCK_SIZE];
+        write_header(&mut commit_block, BlockType::Commit, sequence);
+        let now = now();
+        commit_block[COMMIT_SEC..COMMIT_SEC + 8].copy_from_slice(&now.as_secs().to_be_bytes());
+        write_be32(&mut commit_block, COMMIT_NSEC, now.subsec_nanos());
+        super_block.set_commit_checksum(&mut commit_block);
+        wait_bios(&self.write_block_async(jblock, &commit_block)?)?;
+        self.flush()?;
+
+        self.checkpoint(&blocks)?;
+
+        super_block.sequence = sequence.wrapping_add(1);
+        super_block.start = 0;
+        self.write_super_block(super_block)?;
+        self.flush()
+    }
+
+    /// Writes the blocks to their home locations.
+    fn checkpoint(&self, blocks: &[(&Ext2Bid, &Vec<u8>)]) -> Result<()> {
+        let mut bio_waiter = BioWaiter::new();
+        for (home_bid, block) in blocks {
+            bio_waiter.concat(
+                self.block_device
+                    .write_bytes_async(Bid::new(**home_bid as u64).to_offset(), block)?,
+            );
+        }
+        wait_bios(&bio_waiter)?;
+        self.flush()
+    }
+
+    fn write_super_block(&self, super_block: &mut JournalSuperBlock) -> Result<()> {
+        super_block.update_raw();
+        wait_bios(&self.write_block_async(0, &super_block.raw)?)
+    }
+
+    fn read_block(&self, jblock: u32, buf: &mut [u8]) -> Result<()> {
+        let bid = self.device_bid(jblock)?;
+        self.block_device
+            .read_bytes(Bid::new(bid as u64).to_offset(), buf)?;
+        Ok(())
+    }
+
+    fn write_block_async(&self, jblock: u32, buf: &[u8]) -> Result<BioWaiter> {
+        let bid = self.device_bid(jblock)?;
+        let waiter = self
+            .block_device
+            .write_bytes_async(Bid::new(bid as u64).to_offset(), buf)?;
+        Ok(waiter)
+    }
+
+    fn device_bid(&self, jblock: u32) -> Result<Ext2Bid> {
+        self.blocks
+            .get(jblock as usize)
+            .copied()
+            .ok_or_else(|| Error::with_message(Errno::EUCLEAN, "journal block out of range"))
+    }
+
+    fn flush(&self) -> Result<()> {
+        match self.block_device.sync()? {
+            BioStatus::Complete => Ok(()),
+            err_status => Err(Error::from(err_status)),
+        }
+    }
+}
+
+/// The passes of the recovery.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum RecoveryPass {
+    /// Finds the end of the log.
+    Scan,
+    /// Collects the revoked blocks before the given sequence.
+    Revoke(u32),
+    /// Writes the logged blocks to their home locations before the given sequence.
+    Replay(u32),
+}
+
+impl RecoveryPass {
+    fn end_sequence(&self) -> Option<u32> {
+        match self {
+            Self::Scan => None,
+            Self::Revoke(end_sequence) | Self::Replay(end_sequence) => Some(*end_sequence),
+        }
+    }
+}
+
+/// A tag in the descriptor block, which describes a logged block.
+#[derive(Clone, Copy, Debug)]
+struct Tag {
+    home_bid: u64,
+    flags: TagFlags,
+    checksum: u32,
+}
+
+/// The superblock of the journal, which is the first block of the journal.
+#[derive(Debug)]
+struct JournalSuperBlock {
+    /// The raw block, whose uninterpreted fields are written back unchanged.
+    raw: Vec<u8>,
+    /// The first block of the log
+    first: u32,
+    /// The block next to the last block of the log
+    last: u32,
+    /// The sequence of the first transaction in the log
+    sequence: u32,
+    /// The first block of the log in use, or zero if the log is empty
+    start: u32,
+    incompat: JournalIncompatFeatures,
+    /// The seed of the checksums if version 2 or 3 of the checksums is enabled
+    csum_seed: Option<u32>,
+}
+
+impl JournalSuperBlock {
+    fn parse(raw: Vec<u8>, nblocks: usize) -> Result<Self> {
+        if read_be32(&raw, 0) != JBD2_MAGIC {
+            return_errno_with_message!(Errno::EINVAL, "bad journal magic");
+        }
+        let block_type = BlockType::try_from(read_be32(&raw, 4))
+            .map_err(|_| Error::with_message(Errno::EINVAL, "bad journal superblock type"))?;
+        let incompat = match block_type {
+            BlockType::SuperBlockV1 => JournalIncompatFeatures::empty(),
+            BlockType::SuperBlockV2 => {
+                JournalIncompatFeatures::from_bits(read_be32(&raw, SB_FEATURE_INCOMPAT))
+                    .ok_or_else(|| {
+                        Error::with_message(Errno::EINVAL, "unsupported journal features")
+                    })?
+            }
+            _ => return_errno_with_message!(Errno::EINVAL, "bad journal superblock type"),
+        };
+        if read_be32(&raw, SB_BLOCK_SIZE) as usize != BLOCK_SIZE {
+            return_errno_with_message!(Errno::EINVAL, "unsupported journal block size");
+        }
+
+        let csum_seed = if incompat
+            .intersects(JournalIncompatFeatures::CSUM_V2 | JournalIncompatFeatures::CSUM_V3)
+        {
+            if raw[SB_CHECKSUM_TYPE] != CHECKSUM_TYPE_CRC32C {
+                return_errno_with_message!(Errno::EINVAL, "unsupported journal checksum type");
+            }
+            if read_be32(&raw, SB_CHECKSUM) != super_block_checksum(&raw) {
+                return_errno_with_message!(Errno::EUCLEAN, "bad journal superblock checksum");
+            }
+            Some(crc32c(!0, &raw[SB_UUID..SB_UUID + UUID_LEN]))
+        } else {
+            None
+        };
+
+        let max_len = (read_be32(&raw, SB_MAX_LEN) as usize).min(nblocks) as u32;
+        let fast_commit_blocks = if incompat.contains(JournalIncompatFeatures::FAST_COMMIT) {
+            match read_be32(&raw, SB_NUM_FC_BLOCKS) {
+                0 => DEFAULT_FAST_COMMIT_BLOCKS,
+                nblocks => nblocks,
+            }
+        } else {
+            0
+        };
+        let first = read_be32(&raw, SB_FIRST);
+        let last = max_len.saturating_sub(fast_commit_blocks);
+        if first == 0 || first >= last {
+            return_errno_with_message!(Errno::EINVAL, "bad journal geometry");
+        }
+
+        Ok(Self {
+            first,
+            last,
+            sequence: read_be32(&raw, SB_SEQUENCE),
+            start: read_be32(&raw, SB_START),
+            incompat,
+            csum_seed,
+            raw,
+        })
+    }
+
+    /// Writes the sequence and the start of the log back to the raw block.
+    fn update_raw(&mut self) {
+        write_be32(&mut self.raw, SB_SEQUENCE, self.sequence);
+        write_be32(&mut self.raw, SB_START, self.start);
+        if self.csum_seed.is_some() {
+            let checksum = super_block_checksum(&self.raw);
+            write_be32(&mut self.raw, SB_CHECKSUM, checksum);
+        }
+    }
+
+    fn uuid(&self) -> [u8; UUID_LEN] {
+        self.raw[SB_UUID..SB_UUID + UUID_LEN].try_into().unwrap()
+    }
+
+    /// Returns the number of blocks in the log.
+    fn log_len(&self) -> usize {
+        (self.last - self.first) as usize
+    }
+
+    /// Returns the maximum number of blocks in a transaction.
+    fn max_transaction_blocks(&self) -> usize {
+        (self.log_len() / 4).max(1)
+    }
+
+    fn next_block(&self, jblock: u32) -> u32 {
+        if jblock + 1 >= self.last {
+            self.first
+        } else {
+            jblock + 1
+        }
+    }
+
+    fn tag_len(&self) -> usize {
+        if self.incompat.contains(JournalIncompatFeatures::CSUM_V3) {
+            return 16;
+        }
+        let mut len = 8;
+        if self.incompat.contains(JournalIncompatFeatures::CSUM_V2) {
+            len += 2;
+        }
+        if self.incompat.contains(JournalIncompatFeatures::IS_64BIT) {
+            len += 4;
+        }
+        len
+    }
+
+    fn tail_len(&self) -> usize {
+        if self.csum_seed.is_some() {
+            BLOCK_TAIL_LEN
+        } else {
+            0
+        }
+    }
+
+    fn tags_per_descriptor(&self) -> usize {
+        (BLOCK_SIZE - HEADER_LEN - UUID_LEN - self.tail_len()) / self.tag_len()
+    }
+
+    fn parse_tags(&self, descriptor: &[u8]) -> Vec<Tag> {
+        let tag_len = self.tag_len();
+        let end = BLOCK_SIZE - self.tail_len();
+        let mut tags = Vec::new();
+        let mut offset = HEADER_LEN;
+        while offset + tag_len <= end {
+            let tag = self.read_tag(&descriptor[offset..offset + tag_len]);
+            offset += tag_len;
+            if !tag.flags.contains(TagFlags::SAME_UUID) {
+                offset += UUID_LEN;
+            }
+            tags.push(tag);
+            if tag.flags.contains(TagFlags::LAST_TAG) {
+                break;
+            }
+        }
+        tags
+    }
+
+    fn read_tag(&self, buf: &[u8]) -> Tag {
+        let is_64bit = self.incompat.contains(JournalIncompatFeatures::IS_64BIT);
+        if self.incompat.contains(JournalIncompatFeatures::CSUM_V3) {
+            let high = if is_64bit { read_be32(buf, 8) } else { 0 };
+            return Tag {
+                home_bid: ((high as u64) << 32) | read_be32(buf, 0) as u64,
+                flags: TagFlags::from_bits_truncate(read_be32(buf, 4)),
+                checksum: read_be32(buf, 12),
+            };
+        }
+
+        let high = if is_64bit { read_be32(buf, 8) } else { 0 };
+        Tag {
+            home_bid: ((high as u64) << 32) | read_be32(buf, 0) as u64,
+            flags: TagFlags::from_bits_truncate(read_be16(buf, 6) as u32),
+            checksum: read_be16(buf, 4) as u32,
+        }
+    }
+
+    /// Writes the tag to `buf`, and returns the length of the tag.
+    fn write_tag(&self, buf: &mut [u8], tag: &Tag) -> usize {
+        let tag_len = self.tag_len();
+        buf[..tag_len].fill(0);
+        write_be32(buf, 0, tag.home_bid as u32);
+        if self.incompat.contains(JournalIncompatFeatures::IS_64BIT) {
+            write_be32(buf, 8, (tag.home_bid >> 32) as u32);
+        }
+        if self.incompat.contains(JournalIncompatFeatures::CSUM_V3) {
+            write_be32(buf, 4, tag.flags.bits());
+            write_be32(buf, 12, tag.checksum);
+        } else {
+            buf[4..6].copy_from_slice(&(tag.checksum as u16).to_be_bytes());
+            buf[6..8].copy_from_slice(&(tag.flags.bits() as u16).to_be_bytes());
+        }
+        tag_len
+    }
+
+    fn parse_revoke_records(&self, revoke_block: &[u8]) -> Vec<u64> {
+        let record_len = if self.incompat.contains(JournalIncompatFeatures::IS_64BIT) {
+            8
+        } else {
+            4
+        };
+        let count =
+            (read_be32(revoke_block, REVOKE_COUNT) as usize).min(BLOCK_SIZE - self.tail_len());
+        let mut records = Vec::new();
+        let mut offset = REVOKE_COUNT + 4;
+        while offset + record_len <= count {
+            let bid = if record_len == 8 {
+                ((read_be32(revoke_block, offset) as u64) << 32)
+                    | read_be32(revoke_block, offset + 4) as u64
+            } else {
+                read_be32(revoke_block, offset) as u64
+            };
+            records.push(bid);
+            offset += record_len;
+        }
+        records
+    }
+
+    /// Computes the checksum of a logged block in the transaction of `sequence`.
+    fn tag_checksum(&self, sequence: u32, data: &[u8]) -> u32 {
+        let Some(csum_seed) = self.csum_seed else {
+            return 0;
+        };
+        let checksum = crc32c(crc32c(csum_seed, &sequence.to_be_bytes()), data);
+        if self.incompat.contains(JournalIncompatFeatures::CSUM_V3) {
+            checksum
+        } else {
+            checksum & 0xffff
+        }
+    }
+
+    fn verify_tag(&self, tag: &Tag, sequence: u32, data: &[u8]) -> bool {
+        self.csum_seed.is_none() || self.tag_checksum(sequence, data) == tag.checksum
+    }
+
+    fn block_tail_checksum(&self, csum_seed: u32, block: &[u8]) -> u32 {
+        let tail_offset = BLOCK_SIZE - BLOCK_TAIL_LEN;
+        crc32c(
+            crc32c(csum_seed, &block[..tail_offset]),
+            &[0u8; BLOCK_TAIL_LEN],
+        )
+    }
+
+    fn verify_block_tail(&self, block: &[u8]) -> bool {
+        let Some(csum_seed) = self.csum_seed else {
+            return true;
+        };
+        read_be32(block, BLOCK_SIZE - BLOCK_TAIL_LEN) == self.block_tail_checksum(csum_seed, block)
+    }
+
+    fn set_block_tail(&self, block: &mut [u8]) {
+        if let Some(csum_seed) = self.csum_seed {
+            let checksum = self.block_tail_checksum(csum_seed, block);
+            write_be32(block, BLOCK_SIZE - BLOCK_TAIL_LEN, checksum);
+        }
+    }
+
+    fn commit_checksum(&self, csum_seed: u32, block: &[u8]) -> u32 {
+        let mut checksum = crc32c(csum_seed, &block[..COMMIT_CHECKSUM]);
+        checksum = crc32c(checksum, &[0u8; 4]);
+        crc32c(checksum, &block[COMMIT_CHECKSUM + 4..])
+    }
+
+    fn verify_commit_block(&self, block: &[u8]) -> bool {
+        let Some(csum_seed) = self.csum_seed else {
+            return true;
+        };
+        read_be32(block, COMMIT_CHECKSUM) == self.commit_checksum(csum_seed, block)
+    }
+
+    fn set_commit_checksum(&self, block: &mut [u8]) {
+        if let Some(csum_seed) = self.csum_seed {
+            let checksum = self.commit_checksum(csum_seed, block);
+            write_be32(block, COMMIT_CHECKSUM, checksum);
+        }
+    }
+}
+
+fn super_block_checksum(raw: &[u8]) -> u32 {
+    let mut checksum = crc32c(!0, &raw[..SB_CHECKSUM]);
+    checksum = crc32c(checksum, &[0u8; 4]);
+    crc32c(checksum, &raw[SB_CHECKSUM + 4..SB_LEN])
+}
+
+fn write_header(block: &mut [u8], block_type: BlockType, sequence: u32) {
+    write_be32(block, 0, JBD2_MAGIC);
+    write_be32(block, 4, block_type as u32);
+    write_be32(block, 8, sequence);
+}
+
+fn wait_bios(bio_waiter: &BioWaiter) -> Result<()> {
+    bio_waiter
+        .wait()
+        .ok_or_else(|| Error::with_message(Errno::EIO, "failed to write journal"))?;
+    Ok(())
+}
+
+/// Returns whether the transaction sequence `x` is after `y`, considering the wrap around.
+fn tid_gt(x: u32, y: u32) -> bool {
+    (x.wrapping_sub(y) as i32) > 0
+}
+
+fn read_be32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_be16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes(buf[offset..offset + 2].try_into().unwrap())
+}
+
+fn write_be32(buf: &mut [u8], offset: usize, val: u32) {
+    buf[offset..offset + 4].copy_from_slice(&val.to_be_bytes());
+}
@@ -14,6 +14,11 @@
 //!    stored in PageCache, which accelerates the performance of data access.
 //! 3. Compatible with queue-based block device. The filesystem can submits multiple
 //!    BIO requests to be block device at once, thereby enhancing I/O performance.
+//! 4. Reads and writes Ext4 images. The files mapped by extents, the hash tree indexes
+//!    of directories, the metadata checksums and the flexible block groups are supported.
+//! 5. Journaling of metadata. If the filesystem has a JBD2 journal, the journal is
+//!    recovered on opening, and the metadata are committed to the journal before they
+//!    are written in place, as the `data=ordered` mode of Linux.
 //!
 //! # Example
 //!
@@ -35,6 +40,11 @@
 //! Here we summarizes the features that need to be implemented in the future.
 //! 1. Supports merging small read/write operations.
 //! 2. Handles the intermediate failure status correctly.
+//! 3. Keeps the hash tree indexes of directories up-to-date. Currently, the index of
+//!    a directory is dropped once the directory is modified.
+//! 4. Supports the inline data, the in-inode extended attributes, the blocks beyond
+//!    2^32 and the block sizes other than 4096 bytes.
+//! 5. Replays the fast commits of the journal and processes the orphan list.
 
 pub use fs::Ext2;
 pub use inode::{FilePerm, Inode};
@@ -42,11 +52,15 @@ pub use super_block::{SuperBlock, MAGIC_NUM};
 
 mod block_group;
 mod block_ptr;
+pub(in crate::fs) mod csum;
 mod dir;
+mod extent;
 mod fs;
+mod htree;
 mod impl_for_vfs;
 mod indirect_block_cache;
 mod inode;
+mod journal;
 mod prelude;
 mod super_block;
 mod utils;
@@ -2,7 +2,7 @@
 
 use ostd::const_assert;
 
-use super::{inode::RawInode, prelude::*};
+use super::{csum::crc32c, inode::RawInode, prelude::*};
 
 /// The magic number of Ext2.
 pub const MAGIC_NUM: u16 = 0xef53;
@@ -93,12 +93,79 @@ pub struct SuperBlock {
     prealloc_file_blocks: u8,
     /// Number of blocks to preallocate for directories.
     prealloc_dir_blocks: u8,
+    /// Number of reserved GDT entries for future filesystem expansion.
+    reserved_gdt_blocks: u16,
+    ///
+    /// These fields are for journaling support in Ext3.
+    ///
+    /// Inode number of journal file.
+    journal_ino: u32,
+    /// HTREE hash seed.
+    hash_seed: [u32; 4],
+    /// Default hash version to use.
+    def_hash_version: u8,
+    ///
+    /// These fields are for the features of Ext4.
+    ///
+    /// Size of the group descriptors.
+    desc_size: usize,
+    /// Miscellaneous flags.
+    flags: SuperBlockFlags,
+    /// Number of block groups in a flexible block group is `1 << log_groups_per_flex`.
+    log_groups_per_flex: u8,
+    /// New inodes should reserve this many bytes beyond the 128-byte base.
+    want_extra_isize: u16,
+    /// Block groups containing the backups if the FeatureCompatSet::SPARSE_SUPER2 is set.
+    backup_bgs: [u32; 2],
+    /// The seed of the metadata checksums if the FeatureRoCompatSet::METADATA_CSUM is set.
+    csum_seed: Option<u32>,
+    /// The raw superblock, whose uninterpreted fields are written back unchanged.
+    raw: RawSuperBlock,
 }
 
 impl TryFrom<RawSuperBlock> for SuperBlock {
     type Error = crate::error::Error;
 
     fn try_from(sb: RawSuperBlock) -> Result<Self> {
+        let feature_compat = FeatureCompatSet::from_bits_truncate(sb.feature_compat);
+        let feature_incompat = FeatureInCompatSet::from_bits(sb.feature_incompat).ok_or(
+            Error::with_message(Errno::EINVAL, "invalid feature incompat set"),
+        )?;
+        if feature_incompat.intersects(FeatureInCompatSet::unsupported()) {
+            return_errno_with_message!(Errno::EINVAL, "not supported feature incompat set");
+        }
+        let feature_ro_compat = FeatureRoCompatSet::from_bits(sb.feature_ro_compat).ok_or(
+            Error::with_message(Errno::EINVAL, "invalid feature ro compat set"),
+        )?;
+        if feature_ro_compat.intersects(FeatureRoCompatSet::unsupported()) {
+            return_errno_with_message!(Errno::EINVAL, "not supported feature ro compat set");
+        }
+
+        let is_64bit = feature_incompat.contains(FeatureInCompatSet::IS_64BIT);
+        if is_64bit
+            && (sb.blocks_count_hi != 0
+                || sb.reserved_blocks_count_hi != 0
+                || sb.free_blocks_count_hi != 0)
+        {
+            return_errno_with_message!(Errno::EINVAL, "more than 2^32 blocks is not supported");
+        }
+
+        let csum_seed = if feature_ro_compat.contains(FeatureRoCompatSet::METADATA_CSUM) {
+            if sb.checksum_type != CHECKSUM_TYPE_CRC32C {
+                return_errno_with_message!(Errno::EINVAL, "unknown checksum type");
+            }
+            if sb.checksum != sb.compute_checksum() {
+                return_errno_with_message!(Errno::EINVAL, "bad superblock checksum");
+            }
+            if feature_incompat.contains(FeatureInCompatSet::CSUM_SEED) {
+                Some(sb.checksum_seed)
+            } else {
+                Some(crc32c(!0, &sb.uuid))
+            }
+        } else {
+            None
+        };
+
         Ok(Self {
             inodes_count: sb.inodes_count,
             blocks_count: sb.blocks_count,
@@ -160,20 +227,42 @@ impl TryFrom<RawSuperBlock> for SuperBlock {
                 inode_size
             },
             block_group_idx: sb.block_group_idx as _,
-            feature_compat: FeatureCompatSet::from_bits(sb.feature_compat).ok_or(
-                Error::with_message(Errno::EINVAL, "invalid feature compat set"),
-            )?,
-            feature_incompat: FeatureInCompatSet::from_bits(sb.feature_incompat).ok_or(
-                Error::with_message(Errno::EINVAL, "invalid feature incompat set"),
-            )?,
-            feature_ro_compat: FeatureRoCompatSet::from_bits(sb.feature_ro_compat).ok_or(
-                Error::with_message(Errno::EINVAL, "invalid feature ro compat set"),
-            )?,
+            feature_compat,
+            feature_incompat,
+            feature_ro_compat,
             uuid: sb.uuid,
             volume_name: sb.volume_name,
             last_mounted_dir: sb.last_mounted_dir,
             prealloc_file_blocks: sb.prealloc_file_blocks,
             prealloc_dir_blocks: sb.prealloc_dir_blocks,
+            reserved_gdt_blocks: sb.reserved_gdt_blocks,
+            journal_ino: sb.journal_ino,
+            hash_seed: sb.hash_seed,
+            def_hash_version: sb.def_hash_version,
+            desc_size: {
+                if is_64bit {
+                    let desc_size = sb.desc_size as usize;
+                    if desc_size < 64 || desc_size > BLOCK_SIZE || !desc_size.is_power_of_two() {
+                        return_errno_with_message!(Errno::EINVAL, "invalid group descriptor size");
+                    }
+                    desc_size
+                } else {
+                    32
+                }
+            },
+            flags: SuperBlockFlags::from_bits_truncate(sb.flags),
+            log_groups_per_flex: {
+                if feature_incompat.contains(FeatureInCompatSet::FLEX_BG)
+                    && sb.log_groups_per_flex >= 32
+                {
+                    return_errno_with_message!(Errno::EINVAL, "invalid flex group size");
+                }
+                sb.log_groups_per_flex
+            },
+            want_extra_isize: sb.want_extra_isize,
+            backup_bgs: sb.backup_bgs,
+            csum_seed,
+            raw: sb,
         })
     }
 }
@@ -215,8 +304,10 @@ impl SuperBlock {
     }
 
     /// Returns the number of block groups.
+    ///
+    /// The last block group may contain fewer blocks than the others.
     pub fn block_groups_count(&self) -> u32 {
-        self.blocks_count / self.blocks_per_group
+        (self.blocks_count - self.first_data_block.to_raw() as u32).div_ceil(self.blocks_per_group)
     }
 
     /// Returns the filesystem state.
@@ -275,10 +366,113 @@ impl SuperBlock {
         self.free_inodes_count -= 1;
     }
 
+    /// Returns the size of the group descriptors.
+    pub fn desc_size(&self) -> usize {
+        self.desc_size
+    }
+
+    /// Returns the number of blocks occupied by the group descriptor table.
+    pub fn group_descriptors_blocks(&self) -> usize {
+        (self.block_groups_count() as usize * self.desc_size).div_ceil(self.block_size)
+    }
+
+    /// Returns the number of blocks reserved for the growth of the group descriptor table.
+    pub fn reserved_gdt_blocks(&self) -> usize {
+        self.reserved_gdt_blocks as usize
+    }
+
+    /// Returns the number of block groups in a flexible block group.
+    pub fn groups_per_flex(&self) -> usize {
+        if self.feature_incompat.contains(FeatureInCompatSet::FLEX_BG) {
+            1 << self.log_groups_per_flex
+        } else {
+            1
+        }
+    }
+
+    /// Returns the 128-bit uuid of the volume.
+    pub fn uuid(&self) -> &[u8; 16] {
+        &self.uuid
+    }
+
+    /// Returns whether the filesystem has a journal.
+    pub fn has_journal(&self) -> bool {
+        self.feature_compat.contains(FeatureCompatSet::HAS_JOURNAL)
+    }
+
+    /// Returns the inode number of the journal file.
+    pub fn journal_ino(&self) -> u32 {
+        self.journal_ino
+    }
+
+    /// Returns whether the journal may contain transactions to replay.
+    pub fn needs_recovery(&self) -> bool {
+        self.feature_incompat.contains(FeatureInCompatSet::RECOVER)
+    }
+
+    /// Marks that the journal may contain transactions to replay.
+    pub(super) fn set_needs_recovery(&mut self) {
+        self.feature_incompat.insert(FeatureInCompatSet::RECOVER);
+    }
+
+    /// Returns whether the files can map their blocks with extent trees.
+    pub fn has_extents(&self) -> bool {
+        self.feature_incompat.contains(FeatureInCompatSet::EXTENTS)
+    }
+
+    /// Returns whether the directories can be indexed with hash trees.
+    pub fn has_dir_index(&self) -> bool {
+        self.feature_compat.contains(FeatureCompatSet::DIR_INDEX)
+    }
+
+    /// Returns the seed of the metadata checksums,
+    /// or `None` if the metadata checksums are disabled.
+    pub fn csum_seed(&self) -> Option<u32> {
+        self.csum_seed
+    }
+
+    /// Returns whether the group descriptors have checksums,
+    /// which allow the bitmaps and inode tables to be left uninitialized.
+    pub fn has_group_desc_csum(&self) -> bool {
+        self.feature_ro_compat
+            .intersects(FeatureRoCompatSet::GDT_CSUM | FeatureRoCompatSet::METADATA_CSUM)
+    }
+
+    /// Returns the HTREE hash seed.
+    pub fn hash_seed(&self) -> &[u32; 4] {
+        &self.hash_seed
+    }
+
+    /// Returns whether the legacy, half MD4 and TEA directory hashes
+    /// treat the names as unsigned chars.
+    pub fn is_hash_unsigned(&self) -> bool {
+        self.flags.contains(SuperBlockFlags::UNSIGNED_HASH)
+    }
+
+    /// Returns the size of the extra space that new inodes reserve beyond the 128-byte base.
+    pub fn new_inode_extra_isize(&self) -> u16 {
+        const GOOD_EXTRA_ISIZE: u16 = 32;
+
+        let max_extra_isize = (self.inode_size - core::mem::size_of::<RawInode>()) as u16;
+        if self.want_extra_isize != 0 {
+            self.want_extra_isize.min(max_extra_isize)
+        } else {
+            GOOD_EXTRA_ISIZE.min(max_extra_isize)
+        }
+    }
+
     /// Checks if the block group will backup the super block.
     pub(super) fn is_backup_group(&self, block_group_idx: usize) -> bool {
         if block_group_idx == 0 {
             false
+        } else if self
+            .feature_compat
+            .contains(FeatureCompatSet::SPARSE_SUPER2)
+        {
+            // At most two backup groups are recorded in the superblock.
+            self.backup_bgs
+                .iter()
+                .any(|&idx| idx != 0 && idx as usize == block_group_idx)
         } else if self
             .feature_ro_compat
             .contains(FeatureRoCompatSet::SPARSE_SUPER)
@@ -339,6 +533,32 @@ bitflags! {
         const RESIZE_INO = 1 << 4;
         /// Directories use hash index
         const DIR_INDEX = 1 << 5;
+        /// Block groups may have uninitialized bitmaps and inode tables (obsolete)
+        const LAZY_BG = 1 << 6;
+        /// Exclude inode for snapshots (not used)
+        const EXCLUDE_INODE = 1 << 7;
+        /// Exclude bitmap for snapshots (not used)
+        const EXCLUDE_BITMAP = 1 << 8;
+        /// At most two backups of the superblock
+        const SPARSE_SUPER2 = 1 << 9;
+        /// File system has a fast commit area in the journal
+        const FAST_COMMIT = 1 << 10;
+        /// Inode numbers never change
+        const STABLE_INODES = 1 << 11;
+        /// File system has an orphan file
+        const ORPHAN_FILE = 1 << 12;
+    }
+}
+
+bitflags! {
+    /// Miscellaneous flags of the superblock.
+    pub struct SuperBlockFlags: u32 {
+        /// Signed directory hash in use
+        const SIGNED_HASH = 1 << 0;
+        /// Unsigned directory hash in use
+        const UNSIGNED_HASH = 1 << 1;
+        /// To test development code
+        const TEST_FILESYS = 1 << 2;
     }
 }
 
@@ -355,6 +575,43 @@ bitflags! {
         const JOURNAL_DEV = 1 << 3;
         /// Metablock block group
         const META_BG = 1 << 4;
+        /// Files use extent trees
+        const EXTENTS = 1 << 6;
+        /// File system can have more than 2^32 blocks
+        const IS_64BIT = 1 << 7;
+        /// Multiple mount protection
+        const MMP = 1 << 8;
+        /// Flexible block groups
+        const FLEX_BG = 1 << 9;
+        /// Inodes can be used to store large extended attributes
+        const EA_INODE = 1 << 10;
+        /// Data in directory entries
+        const DIRDATA = 1 << 12;
+        /// Metadata checksum seed is stored in the superblock
+        const CSUM_SEED = 1 << 13;
+        /// Large directories (more than 2GiB or 3-level hash trees)
+        const LARGEDIR = 1 << 14;
+        /// Data in inodes
+        const INLINE_DATA = 1 << 15;
+        /// Encrypted inodes are present
+        const ENCRYPT = 1 << 16;
+        /// Case-insensitive directories
+        const CASEFOLD = 1 << 17;
+    }
+}
+
+impl FeatureInCompatSet {
+    /// Returns the features that are recognized but not supported.
+    fn unsupported() -> Self {
+        Self::COMPRESSION
+            | Self::JOURNAL_DEV
+            | Self::META_BG
+            | Self::MMP
+            | Self::EA_INODE
+            | Self::DIRDATA
+            | Self::INLINE_DATA
+            | Self::ENCRYPT
+            | Self::CASEFOLD
     }
 }
 
@@ -367,6 +624,48 @@ bitflags! {
         const LARGE_FILE = 1 << 1;
         /// Directory contents are stored in the form of a Binary Tree
         const BTREE_DIR = 1 << 2;
+        /// File sizes can be represented in units of file system blocks
+        const HUGE_FILE = 1 << 3;
+        /// Group descriptors have checksums
+        const GDT_CSUM = 1 << 4;
+        /// Directories can have more than 65000 subdirectories
+        const DIR_NLINK = 1 << 5;
+        /// Inodes can have extra fields
+        const EXTRA_ISIZE = 1 << 6;
+        /// File system has a snapshot
+        const HAS_SNAPSHOT = 1 << 7;
+        /// Quota is handled transactionally with the journal
+        const QUOTA = 1 << 8;
+        /// Blocks are allocated in clusters
+        const BIGALLOC = 1 << 9;
+        /// Metadata has checksums
+        const METADATA_CSUM = 1 << 10;
+        /// Replicas (not used)
+        const REPLICA = 1 << 11;
+        /// File system can only be mounted read-only
+        const READONLY = 1 << 12;
+        /// Project quotas
+        const PROJECT = 1 << 13;
+        /// Blocks can be shared by files
+        const SHARED_BLOCKS = 1 << 14;
+        /// Verity inodes may be present
+        const VERITY = 1 << 15;
+        /// Orphan file may be non-empty
+        const ORPHAN_PRESENT = 1 << 16;
+    }
+}
+
+impl FeatureRoCompatSet {
+    /// Returns the features that are recognized but not supported.
+    fn unsupported() -> Self {
+        Self::HAS_SNAPSHOT
+            | Self::QUOTA
+            | Self::BIGALLOC
+            | Self::REPLICA
+            | Self::READONLY
+            | Self::PROJECT
+            | Self::SHARED_BLOCKS
+            | Self::ORPHAN_PRESENT
     }
 }
 
@@ -417,11 +716,14 @@ pub enum RevLevel {
     Dynamic = 1,
 }
 
+/// The type of the metadata checksums.
+const CHECKSUM_TYPE_CRC32C: u8 = 1;
+
 const_assert!(core::mem::size_of::<RawSuperBlock>() == SUPER_BLOCK_SIZE);
 
 /// The raw superblock, it must be exactly 1024 bytes in length.
 #[repr(C)]
-#[derive(Clone, Copy, Debug, Pod, Default)]
+#[derive(Clone, Copy, Debug, Pod)]
 pub(super) struct RawSuperBlock {
     pub inodes_count: u32,
     pub blocks_count: u32,
@@ -465,7 +767,8 @@ pub(super) struct RawSuperBlock {
     pub algorithm_usage_bitmap: u32,
     pub prealloc_file_blocks: u8,
     pub prealloc_dir_blocks: u8,
-    padding1: u16,
+    /// Number of reserved GDT entries for future filesystem expansion.
+    pub reserved_gdt_blocks: u16,
     ///
     /// This fields are for journaling support in Ext3.
     ///
@@ -481,13 +784,65 @@ pub(super) struct RawSuperBlock {
     pub hash_seed: [u32; 4],
     /// Default hash version to use
     pub def_hash_version: u8,
-    reserved_char_pad: u8,
-    reserved_word_pad: u16,
+    pub journal_backup_type: u8,
+    /// Size of group descriptors if the FeatureInCompatSet::IS_64BIT is set.
+    pub desc_size: u16,
     /// Default mount options.
     pub default_mount_opts: u32,
     /// First metablock block group.
     pub first_meta_bg: u32,
-    reserved: Reserved,
+    ///
+    /// These fields are for the features of Ext4.
+    ///
+    /// When the filesystem was created.
+    pub mkfs_time: u32,
+    /// Backup of the journal inode's block pointers and size.
+    pub journal_blocks: [u32; 17],
+    pub blocks_count_hi: u32,
+    pub reserved_blocks_count_hi: u32,
+    pub free_blocks_count_hi: u32,
+    /// All inodes have at least this many extra bytes.
+    pub min_extra_isize: u16,
+    /// New inodes should reserve this many extra bytes.
+    pub want_extra_isize: u16,
+    /// Miscellaneous flags.
+    pub flags: u32,
+    pub raid_stride: u16,
+    pub mmp_interval: u16,
+    pub mmp_block: u64,
+    pub raid_stripe_width: u32,
+    /// The size of a flexible block group is `1 << log_groups_per_flex`.
+    pub log_groups_per_flex: u8,
+    pub checksum_type: u8,
+    pub encryption_level: u8,
+    reserved_pad: u8,
+    /// Number of KiB written to the filesystem over its lifetime.
+    pub kbytes_written: u64,
+    /// Snapshot, error and mount option information.
+    misc_info: [u32; 51],
+    /// Block groups containing the backups if the FeatureCompatSet::SPARSE_SUPER2 is set.
+    pub backup_bgs: [u32; 2],
+    /// Encryption and quota information.
+    encryption_info: [u32; 7],
+    /// The seed of the metadata checksums if the FeatureInCompatSet::CSUM_SEED is set.
+    pub checksum_seed: u32,
+    reserved: [u32; 98],
+    /// The checksum of the superblock.
+    pub checksum: u32,
+}
+
+impl RawSuperBlock {
+    /// Updates the checksum of the superblock if the metadata checksums are enabled.
+    pub fn update_checksum(&mut self) {
+        if self.feature_ro_compat & FeatureRoCompatSet::METADATA_CSUM.bits() != 0 {
+            self.checksum = self.compute_checksum();
+        }
+    }
+
+    fn compute_checksum(&self) -> u32 {
+        let offset = core::mem::offset_of!(Self, checksum);
+        crc32c(!0, &self.as_bytes()[..offset])
+    }
 }
 
 impl From<&SuperBlock> for RawSuperBlock {
@@ -528,17 +883,7 @@ impl From<&SuperBlock> for RawSuperBlock {
             last_mounted_dir: sb.last_mounted_dir,
             prealloc_file_blocks: sb.prealloc_file_blocks,
             prealloc_dir_blocks: sb.prealloc_dir_blocks,
-            ..Default::default()
+            ..sb.raw
         }
     }
 }
-
-#[repr(C)]
-#[derive(Clone, Copy, Debug, Pod)]
-struct Reserved([u32; 190]);
-
-impl Default for Reserved {
-    fn default() -> Self {
-        Self([0u32; 190])
-    }
-}
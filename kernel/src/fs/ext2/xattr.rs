@@ -2,7 +2,7 @@
 
 use ostd::mm::UntypedMem;
 
-use super::{block_ptr::Ext2Bid, prelude::*, Ext2, Inode};
+use super::{block_ptr::Ext2Bid, csum::crc32c, prelude::*, Ext2, Inode};
 use crate::fs::utils::{XattrName, XattrNamespace, XattrSetFlags, XATTR_NAME_MAX_LEN};
 
 const EXT2_XATTR_MAGIC: u32 = 0xEA020000;
@@ -16,7 +16,8 @@ struct XattrHeader {
     ref_count: u32,
     nblocks: u32,
     hash: u32,
-    reserved: [u32; 4],
+    checksum: u32,
+    reserved: [u32; 3],
 }
 
 const XATTR_HEADER_SIZE: usize = size_of::<XattrHeader>();
@@ -132,8 +133,8 @@ impl Xattr {
             self.inode().set_acl(new_bid);
         // Need to load the xattr block from device
         } else if cache.header.is_none() {
-            fs.block_device().read_blocks(
-                cache.bid,
+            fs.read_blocks(
+                cache.bid.to_raw() as Ext2Bid,
                 BioSegment::new_from_segment(self.blocks_buf.clone(), BioDirection::FromDevice),
             )?;
 
@@ -330,10 +331,16 @@ impl Xattr {
     pub fn flush(&self) -> Result<()> {
         let cache = self.cache.upread();
         if cache.is_dirty() {
-            self.fs().block_device().write_blocks(
-                cache.bid,
+            let fs = self.fs();
+            if let Some(csum_seed) = fs.csum_seed() {
+                self.update_checksum(cache.bid, csum_seed)?;
+            }
+            fs.write_metadata_blocks_async(
+                cache.bid.to_raw() as Ext2Bid,
                 BioSegment::new_from_segment(self.blocks_buf.clone(), BioDirection::ToDevice),
-            )?;
+            )?
+            .wait()
+            .ok_or_else(|| Error::with_message(Errno::EIO, "failed to flush the xattr block"))?;
             cache.upgrade().clear_dirty();
         }
         Ok(())
@@ -345,11 +352,23 @@ impl Xattr {
         if bid == 0 {
             return Ok(());
         }
-        self.fs().free_blocks(bid..bid)?;
+        self.fs().free_blocks(bid..bid + XATTR_NBLOCKS as Ext2Bid)?;
         cache.upgrade().bid = Bid::new(0);
         Ok(())
     }
 
+    /// Updates the checksum in the header, which covers the block number and
+    /// the whole block with the checksum zeroed.
+    fn update_checksum(&self, bid: Bid, csum_seed: u32) -> Result<()> {
+        let checksum_offset = core::mem::offset_of!(XattrHeader, checksum);
+        self.blocks_buf.write_val(checksum_offset, &0u32)?;
+        let mut block = vec![0u8; self.blocks_buf.size()];
+        self.blocks_buf.read_bytes(0, &mut block)?;
+        let csum = crc32c(crc32c(csum_seed, &bid.to_raw().to_le_bytes()), &block);
+        self.blocks_buf.write_val(checksum_offset, &csum)?;
+        Ok(())
+    }
+
     fn fs(&self) -> Arc<Ext2> {
         self.fs.upgrade().unwrap()
     }
@@ -484,6 +503,7 @@ impl Default for XattrHeader {
             nblocks: XATTR_NBLOCKS as _,
             ref_count: Default::default(),
             hash: Default::default(),
+            checksum: Default::default(),
             reserved: Default::default(),
         }
     }
@@ -47,6 +47,15 @@ pub trait FileLike: Pollable + Send + Sync + Any {
         return_errno_with_message!(Errno::EINVAL, "ioctl is not supported");
     }
 
+    /// Handles an ioctl that is not an [`IoctlCmd`].
+    ///
+    /// Some ioctls encode their arguments in the command numbers (e.g., the buffer lengths
+    /// of the `EVIOCG*` ioctls of evdev). They cannot be listed in [`IoctlCmd`], so the raw
+    /// command numbers are passed here.
+    fn ioctl_raw(&self, cmd: u32, arg: usize) -> Result<i32> {
+        return_errno_with_message!(Errno::EINVAL, "the ioctl command is unknown");
+    }
+
     fn resize(&self, new_size: usize) -> Result<()> {
         return_errno_with_message!(Errno::EINVAL, "resize is not supported");
     }
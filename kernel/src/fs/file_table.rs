@@ -19,12 +19,28 @@ use crate::{
     prelude::*,
     process::{
         signal::{constants::SIGIO, signals::kernel::KernelSignal, PollAdaptor},
-        Pid, Process,
+        Pid, Process, ResourceType,
     },
 };
 
 pub type FileDesc = i32;
 
+/// Returns the maximum number of fds, which is limited by the `RLIMIT_NOFILE` of the
+/// current process.
+fn max_nr_fds() -> usize {
+    let Some(process) = Process::current() else {
+        // Kernel threads are not restricted by resource limits.
+        return usize::MAX;
+    };
+
+    process
+        .resource_limits()
+        .get_rlimit(ResourceType::RLIMIT_NOFILE)
+        .get_cur()
+        .try_into()
+        .unwrap_or(usize::MAX)
+}
+
 pub struct FileTable {
     table: SlotVec<FileTableEntry>,
     subject: Subject<FdEvents>,
@@ -81,30 +97,33 @@ impl FileTable {
             .map(|entry| entry.file.clone())
             .ok_or(Error::with_message(Errno::ENOENT, "No such file"))?;
 
-        // Get the lowest-numbered available fd equal to or greater than `new_fd`.
-        let get_min_free_fd = || -> usize {
-            let new_fd = new_fd as usize;
-            if self.table.get(new_fd).is_none() {
-                return new_fd;
-            }
-
-            for idx in new_fd + 1..self.len() {
-                if self.table.get(idx).is_none() {
-                    return idx;
-                }
-            }
-            self.len()
-        };
-
-        let min_free_fd = get_min_free_fd();
+        let min_free_fd = self.alloc_fd(new_fd as usize)?;
         let entry = FileTableEntry::new(file, flags);
         self.table.put_at(min_free_fd, entry);
         Ok(min_free_fd as FileDesc)
     }
 
-    pub fn insert(&mut self, item: Arc<dyn FileLike>, flags: FdFlags) -> FileDesc {
+    /// Inserts a file at the lowest-numbered available fd.
+    ///
+    /// If the fd would exceed the `RLIMIT_NOFILE` of the current process,
+    /// this method fails with `EMFILE`.
+    pub fn insert(&mut self, item: Arc<dyn FileLike>, flags: FdFlags) -> Result<FileDesc> {
+        let fd = self.alloc_fd(0)?;
         let entry = FileTableEntry::new(item, flags);
-        self.table.put(entry) as FileDesc
+        self.table.put_at(fd, entry);
+        Ok(fd as FileDesc)
+    }
+
+    /// Gets the lowest-numbered available fd equal to or greater than `start`.
+    fn alloc_fd(&self, start: usize) -> Result<usize> {
+        let fd = (start..self.len())
+            .find(|idx| self.table.get(*idx).is_none())
+            .unwrap_or(self.len().max(start));
+
+        if fd >= max_nr_fds() {
+            return_errno_with_message!(Errno::EMFILE, "the fd exceeds the file limit");
+        }
+        Ok(fd)
     }
 
     pub fn insert_at(
@@ -134,6 +153,7 @@ impl FileTable {
         if let Some(closed_inode_file) = closed_file.downcast_ref::<InodeHandle>() {
             // FIXME: Operation below should not hold any mutex if `self` is protected by a spinlock externally
             closed_inode_file.release_range_locks();
+            closed_inode_file.dentry().inode().on_fd_close();
         }
         Some(closed_file)
     }
@@ -172,6 +192,7 @@ impl FileTable {
             if let Some(inode_file) = removed_entry.file.downcast_ref::<InodeHandle>() {
                 // FIXME: Operation below should not hold any mutex if `self` is protected by a spinlock externally
                 inode_file.release_range_locks();
+                inode_file.dentry().inode().on_fd_close();
             }
         }
 
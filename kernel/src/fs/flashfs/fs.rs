@@ -0,0 +1,582 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::time::Duration;
+
+use aster_mtd::MtdDevice;
+
+use super::{
+    inode::FlashInode,
+    log::{InodeRecord, Log, Record, ScannedRecord, MAX_DATA_LEN, RESERVED_BLOCKS},
+};
+use crate::{
+    fs::utils::{FileSystem, FsFlags, Inode, InodeMode, InodeType, SuperBlock, NAME_MAX},
+    prelude::*,
+    process::{Gid, Uid},
+    time::clocks::RealTimeCoarseClock,
+};
+
+const FLASHFS_MAGIC: u64 = 0x464c_4653;
+pub(super) const BLOCK_SIZE: usize = 4096;
+pub(super) const ROOT_INO: u64 = 1;
+
+/// The flash filesystem on an MTD device.
+pub struct FlashFs {
+    device: Arc<MtdDevice>,
+    state: Mutex<FsState>,
+}
+
+impl FlashFs {
+    /// Opens the filesystem on the MTD device.
+    ///
+    /// An erased device is an empty filesystem, so the device need not be formatted. A
+    /// device can be mounted multiple times, and all the mounts share the same instance.
+    pub fn open(device: Arc<MtdDevice>) -> Result<Arc<Self>> {
+        static MOUNTED: Mutex<BTreeMap<usize, Weak<FlashFs>>> = Mutex::new(BTreeMap::new());
+
+        let mut mounted = MOUNTED.lock();
+        if let Some(fs) = mounted.get(&device.index()).and_then(Weak::upgrade) {
+            return Ok(fs);
+        }
+
+        let (log, records) = Log::scan(device.clone())?;
+        let nr_records = records.len();
+        let fs = Arc::new_cyclic(|weak_fs| Self {
+            state: Mutex::new(FsState::replay(
+                log,
+                records,
+                weak_fs.clone(),
+                device.is_read_only(),
+            )),
+            device,
+        });
+        info!(
+            "[FLASHFS]: mounted mtd{} ({}) with {} records",
+            fs.device.index(),
+            fs.device.name(),
+            nr_records
+        );
+
+        mounted.insert(fs.device.index(), Arc::downgrade(&fs));
+        Ok(fs)
+    }
+
+    pub(super) fn state(&self) -> MutexGuard<'_, FsState> {
+        self.state.lock()
+    }
+}
+
+impl FileSystem for FlashFs {
+    fn sync(&self) -> Result<()> {
+        // The records are written to the flash when the files are changed.
+        Ok(())
+    }
+
+    fn root_inode(&self) -> Arc<dyn Inode> {
+        self.state().inodes[&ROOT_INO].handle.clone()
+    }
+
+    fn sb(&self) -> SuperBlock {
+        let state = self.state();
+        let capacity = state.log.capacity();
+        let free = capacity.saturating_sub(state.live_len());
+
+        let mut sb = SuperBlock::new(FLASHFS_MAGIC, BLOCK_SIZE, NAME_MAX);
+        sb.blocks = capacity / BLOCK_SIZE;
+        sb.bfree = free / BLOCK_SIZE;
+        sb.bavail = free / BLOCK_SIZE;
+        sb.files = state.inodes.len();
+        sb.fsid = self.device.index() as u64;
+        sb
+    }
+
+    fn flags(&self) -> FsFlags {
+        FsFlags::empty()
+    }
+}
+
+/// The in-memory state of the filesystem, which caches all the inodes and their data.
+pub(super) struct FsState {
+    pub(super) inodes: BTreeMap<u64, InodeData>,
+    log: Log,
+    next_ino: u64,
+    read_only: bool,
+    fs: Weak<FlashFs>,
+}
+
+/// An inode that is cached in memory.
+pub(super) struct InodeData {
+    pub(super) handle: Arc<FlashInode>,
+    pub(super) parent: u64,
+    pub(super) name: String,
+    pub(super) type_: InodeType,
+    pub(super) mode: InodeMode,
+    pub(super) uid: Uid,
+    pub(super) gid: Gid,
+    pub(super) atime: Duration,
+    pub(super) mtime: Duration,
+    pub(super) ctime: Duration,
+    /// The data of the file, or the target of the symbolic link
+    pub(super) data: Vec<u8>,
+    /// The children of the directory
+    pub(super) children: BTreeMap<String, u64>,
+}
+
+impl InodeData {
+    fn new(
+        fs: &Weak<FlashFs>,
+        ino: u64,
+        parent: u64,
+        name: String,
+        type_: InodeType,
+        mode: InodeMode,
+    ) -> Self {
+        let now = RealTimeCoarseClock::get().read_time();
+        Self {
+            handle: FlashInode::new(ino, fs.clone()),
+            parent,
+            name,
+            type_,
+            mode,
+            uid: Uid::new_root(),
+            gid: Gid::new_root(),
+            atime: now,
+            mtime: now,
+            ctime: now,
+            data: Vec::new(),
+            children: BTreeMap::new(),
+        }
+    }
+
+    fn to_record(&self) -> Record {
+        Record::Inode(InodeRecord {
+            parent: self.parent,
+            type_: self.type_,
+            mode: self.mode.bits(),
+            uid: self.uid.into(),
+            gid: self.gid.into(),
+            mtime: self.mtime,
+            ctime: self.ctime,
+            size: self.data.len() as u64,
+            name: self.name.clone(),
+        })
+    }
+}
+
+impl FsState {
+    /// Rebuilds the inodes by replaying the records in the order of their versions.
+    ///
+    /// The inodes that cannot be reached from the root (e.g., those whose parents have been
+    /// removed) are dropped. If two inodes have the same name in a directory, which happens
+    /// if a rename that replaces a file is interrupted, the newer one is kept.
+    fn replay(
+        log: Log,
+        mut records: Vec<ScannedRecord>,
+        fs: Weak<FlashFs>,
+        read_only: bool,
+    ) -> Self {
+        records.sort_unstable_by_key(|record| record.version);
+
+        let root = InodeData::new(
+            &fs,
+            ROOT_INO,
+            ROOT_INO,
+            String::new(),
+            InodeType::Dir,
+            InodeMode::from_bits_truncate(0o755),
+        );
+        let mut state = Self {
+            inodes: BTreeMap::from([(ROOT_INO, root)]),
+            log,
+            next_ino: ROOT_INO + 1,
+            read_only,
+            fs,
+        };
+
+        // The versions of the latest inode records and the sizes in them.
+        let mut latest = BTreeMap::new();
+        for ScannedRecord {
+            version,
+            ino,
+            record,
+        } in records
+        {
+            state.next_ino = state.next_ino.max(ino + 1);
+            match record {
+                Record::Inode(record) => {
+                    if ino == ROOT_INO && record.type_ != InodeType::Dir {
+                        continue;
+                    }
+                    let inode = state.inodes.entry(ino).or_insert_with(|| {
+                        InodeData::new(
+                            &state.fs,
+                            ino,
+                            record.parent,
+                            String::new(),
+                            record.type_,
+                            InodeMode::empty(),
+                        )
+                    });
+                    if ino != ROOT_INO {
+                        inode.parent = record.parent;
+                        inode.name = record.name;
+                    }
+                    inode.type_ = record.type_;
+                    inode.mode = InodeMode::from_bits_truncate(record.mode);
+                    inode.uid = Uid::from(record.uid);
+                    inode.gid = Gid::from(record.gid);
+                    inode.atime = record.mtime;
+                    inode.mtime = record.mtime;
+                    inode.ctime = record.ctime;
+                    inode.data.resize(record.size as usize, 0);
+                    latest.insert(ino, (version, record.size as usize));
+                }
+                Record::Data { offset, bytes } => {
+                    let Some(inode) = state.inodes.get_mut(&ino) else {
+                        continue;
+                    };
+                    let start = offset as usize;
+                    let end = start + bytes.len();
+                    if inode.data.len() < end {
+                        inode.data.resize(end, 0);
+                    }
+                    inode.data[start..end].copy_from_slice(&bytes);
+                }
+                Record::Delete => {
+                    if ino != ROOT_INO {
+                        state.inodes.remove(&ino);
+                        latest.remove(&ino);
+                    }
+                }
+            }
+        }
+
+        // The data after the size was written by an interrupted write.
+        let mut order = Vec::with_capacity(latest.len());
+        for (ino, (version, size)) in latest {
+            state.inodes.get_mut(&ino).unwrap().data.truncate(size);
+            if ino != ROOT_INO {
+                order.push((version, ino));
+            }
+        }
+
+        // Build the directories, where the newer entries replace the older ones.
+        order.sort_unstable();
+        for (_, ino) in order {
+            let inode = &state.inodes[&ino];
+            let (parent, name) = (inode.parent, inode.name.clone());
+            if let Some(dir) = state.inodes.get_mut(&parent) {
+                if dir.type_ == InodeType::Dir {
+                    dir.children.insert(name, ino);
+                }
+            }
+        }
+
+        let mut reachable = BTreeSet::new();
+        let mut stack = vec![ROOT_INO];
+        while let Some(ino) = stack.pop() {
+            if reachable.insert(ino) {
+                stack.extend(state.inodes[&ino].children.values().copied());
+            }
+        }
+        state.inodes.retain(|ino, _| reachable.contains(ino));
+
+        state
+    }
+
+    pub(super) fn get(&self, ino: u64) -> Result<&InodeData> {
+        self.inodes
+            .get(&ino)
+            .ok_or_else(|| Error::with_message(Errno::ENOENT, "the inode has been removed"))
+    }
+
+    fn get_mut(&mut self, ino: u64) -> Result<&mut InodeData> {
+        self.inodes
+            .get_mut(&ino)
+            .ok_or_else(|| Error::with_message(Errno::ENOENT, "the inode has been removed"))
+    }
+
+    /// Returns the number of the bytes that the latest records of the inodes occupy.
+    fn live_len(&self) -> usize {
+        self.inodes
+            .values()
+            .map(|inode| {
+                self.log.inode_record_len(&inode.name) + self.log.data_records_len(inode.data.len())
+            })
+            .sum()
+    }
+
+    /// Ensures that the records of `len` bytes can be appended, collecting the garbage if
+    /// necessary, and that the latest records can grow by `growth` bytes.
+    fn prepare(&mut self, len: usize, growth: usize) -> Result<()> {
+        if self.read_only {
+            return_errno_with_message!(Errno::EROFS, "the MTD device is read-only");
+        }
+        if self.live_len() + growth > self.log.capacity() {
+            return_errno_with_message!(Errno::ENOSPC, "the flash is full");
+        }
+
+        let reserved_len = RESERVED_BLOCKS * self.log.block_size();
+        let mut nr_rounds = self.log.nr_blocks();
+        while self.log.free_len() < len + reserved_len {
+            let Some(victim) = self.log.victim().filter(|_| nr_rounds > 0) else {
+                return_errno_with_message!(Errno::ENOSPC, "no garbage can be collected");
+            };
+            nr_rounds -= 1;
+            self.collect(victim)?;
+        }
+        Ok(())
+    }
+
+    /// Rewrites the latest records of the inodes in the block, and erases the block.
+    ///
+    /// The removal of an inode is rewritten as well if the inode still has older records in
+    /// the other blocks. Otherwise, the inode would come back after a remount.
+    fn collect(&mut self, victim: usize) -> Result<()> {
+        for ino in self.log.inos_of(victim) {
+            match self.inodes.get(&ino) {
+                Some(inode) => {
+                    self.log.append(ino, &inode.to_record())?;
+                    for (index, chunk) in inode.data.chunks(MAX_DATA_LEN).enumerate() {
+                        let record = Record::Data {
+                            offset: (index * MAX_DATA_LEN) as u64,
+                            bytes: chunk.to_vec(),
+                        };
+                        self.log.append(ino, &record)?;
+                    }
+                }
+                None if self.log.has_records_elsewhere(ino, victim) => {
+                    self.log.append(ino, &Record::Delete)?;
+                }
+                None => {}
+            }
+        }
+
+        // The block is marked as bad if it cannot be erased, which does not lose any data.
+        let _ = self.log.erase_block(victim);
+        Ok(())
+    }
+
+    fn write_inode_record(&mut self, ino: u64) -> Result<()> {
+        let record = self.get(ino)?.to_record();
+        self.log.append(ino, &record)
+    }
+
+    pub(super) fn lookup(&self, dir: u64, name: &str) -> Result<Arc<FlashInode>> {
+        let ino = self
+            .get(dir)?
+            .children
+            .get(name)
+            .ok_or_else(|| Error::with_message(Errno::ENOENT, "the file does not exist"))?;
+        Ok(self.inodes[ino].handle.clone())
+    }
+
+    pub(super) fn create(
+        &mut self,
+        dir: u64,
+        name: &str,
+        type_: InodeType,
+        mode: InodeMode,
+    ) -> Result<Arc<FlashInode>> {
+        if name.len() > NAME_MAX {
+            return_errno_with_message!(Errno::ENAMETOOLONG, "the name is too long");
+        }
+        if !matches!(type_, InodeType::File | InodeType::Dir | InodeType::SymLink) {
+            return_errno_with_message!(Errno::EPERM, "the file type is not supported");
+        }
+        if self.get(dir)?.children.contains_key(name) {
+            return_errno_with_message!(Errno::EEXIST, "the file already exists");
+        }
+        let len = self.log.inode_record_len(name);
+        self.prepare(len, len)?;
+
+        let ino = self.next_ino;
+        self.next_ino += 1;
+        let inode = InodeData::new(&self.fs, ino, dir, String::from(name), type_, mode);
+        let handle = inode.handle.clone();
+        self.log.append(ino, &inode.to_record())?;
+        self.inodes.insert(ino, inode);
+
+        let dir = self.get_mut(dir)?;
+        dir.children.insert(String::from(name), ino);
+        let now = RealTimeCoarseClock::get().read_time();
+        dir.mtime = now;
+        dir.ctime = now;
+        Ok(handle)
+    }
+
+    pub(super) fn remove(&mut self, dir: u64, name: &str, is_dir: bool) -> Result<()> {
+        let ino = *self
+            .get(dir)?
+            .children
+            .get(name)
+            .ok_or_else(|| Error::with_message(Errno::ENOENT, "the file does not exist"))?;
+        let inode = self.get(ino)?;
+        match (is_dir, inode.type_ == InodeType::Dir) {
+            (true, false) => return_errno_with_message!(Errno::ENOTDIR, "not a directory"),
+            (false, true) => return_errno_with_message!(Errno::EISDIR, "is a directory"),
+            (true, true) if !inode.children.is_empty() => {
+                return_errno_with_message!(Errno::ENOTEMPTY, "the directory is not empty")
+            }
+            _ => {}
+        }
+        self.prepare(self.log.len_of(&Record::Delete), 0)?;
+
+        self.log.append(ino, &Record::Delete)?;
+        self.inodes.remove(&ino);
+        let dir = self.get_mut(dir)?;
+        dir.children.remove(name);
+        let now = RealTimeCoarseClock::get().read_time();
+        dir.mtime = now;
+        dir.ctime = now;
+        Ok(())
+    }
+
+    pub(super) fn rename(
+        &mut self,
+        old_dir: u64,
+        old_name: &str,
+        new_dir: u64,
+        new_name: &str,
+    ) -> Result<()> {
+        if new_name.len() > NAME_MAX {
+            return_errno_with_message!(Errno::ENAMETOOLONG, "the name is too long");
+        }
+        let ino = *self
+            .get(old_dir)?
+            .children
+            .get(old_name)
+            .ok_or_else(|| Error::with_message(Errno::ENOENT, "the file does not exist"))?;
+        let is_dir = self.get(ino)?.type_ == InodeType::Dir;
+        if self.get(new_dir)?.type_ != InodeType::Dir {
+            return_errno_with_message!(Errno::ENOTDIR, "the target is not a directory");
+        }
+        if is_dir {
+            let mut ancestor = new_dir;
+            while ancestor != ROOT_INO {
+                if ancestor == ino {
+                    return_errno_with_message!(
+                        Errno::EINVAL,
+                        "a directory cannot be moved into itself"
+                    );
+                }
+                ancestor = self.get(ancestor)?.parent;
+            }
+        }
+
+        let replaced = self.get(new_dir)?.children.get(new_name).copied();
+        if let Some(dst) = replaced {
+            if dst == ino {
+                return Ok(());
+            }
+            let dst = self.get(dst)?;
+            match (is_dir, dst.type_ == InodeType::Dir) {
+                (true, true) if !dst.children.is_empty() => {
+                    return_errno_with_message!(Errno::ENOTEMPTY, "the directory is not empty")
+                }
+                (true, false) => return_errno_with_message!(Errno::ENOTDIR, "not a directory"),
+                (false, true) => return_errno_with_message!(Errno::EISDIR, "is a directory"),
+                _ => {}
+            }
+        }
+        let delete_len = replaced.map_or(0, |_| self.log.len_of(&Record::Delete));
+        let len = self.log.inode_record_len(new_name) + delete_len;
+        self.prepare(len, len)?;
+
+        let now = RealTimeCoarseClock::get().read_time();
+        let inode = self.get_mut(ino)?;
+        let old = (
+            inode.parent,
+            core::mem::replace(&mut inode.name, String::from(new_name)),
+        );
+        inode.parent = new_dir;
+        inode.ctime = now;
+        if let Err(err) = self.write_inode_record(ino) {
+            let inode = self.get_mut(ino)?;
+            (inode.parent, inode.name) = old;
+            return Err(err);
+        }
+
+        // If the removal is not written, the replaced file is still dropped when mounting,
+        // since the renamed file is newer.
+        let result = match replaced {
+            Some(dst) => {
+                self.inodes.remove(&dst);
+                self.log.append(dst, &Record::Delete)
+            }
+            None => Ok(()),
+        };
+        for (dir, name, is_new) in [(old_dir, old_name, false), (new_dir, new_name, true)] {
+            let dir = self.get_mut(dir)?;
+            if is_new {
+                dir.children.insert(String::from(name), ino);
+            } else {
+                dir.children.remove(name);
+            }
+            dir.mtime = now;
+            dir.ctime = now;
+        }
+        result
+    }
+
+    /// Writes the bytes at the offset of the file.
+    pub(super) fn write(&mut self, ino: u64, offset: usize, bytes: &[u8]) -> Result<()> {
+        let end = offset
+            .checked_add(bytes.len())
+            .ok_or_else(|| Error::with_message(Errno::EFBIG, "the file is too large"))?;
+        let size = self.get(ino)?.data.len().max(end);
+        self.write_and_resize(ino, offset, bytes, size)
+    }
+
+    /// Sets the target of the symbolic link.
+    pub(super) fn set_link(&mut self, ino: u64, target: &str) -> Result<()> {
+        self.write_and_resize(ino, 0, target.as_bytes(), target.len())
+    }
+
+    /// Changes the size of the file.
+    pub(super) fn resize(&mut self, ino: u64, size: usize) -> Result<()> {
+        self.write_and_resize(ino, 0, &[], size)
+    }
+
+    fn write_and_resize(
+        &mut self,
+        ino: u64,
+        offset: usize,
+        bytes: &[u8],
+        size: usize,
+    ) -> Result<()> {
+        let inode = self.get(ino)?;
+        let len = self.log.data_records_len(bytes.len()) + self.log.inode_record_len(&inode.name);
+        let growth = self
+            .log
+            .data_records_len(size)
+            .saturating_sub(self.log.data_records_len(inode.data.len()));
+        self.prepare(len, growth)?;
+
+        for (index, chunk) in bytes.chunks(MAX_DATA_LEN).enumerate() {
+            let record = Record::Data {
+                offset: (offset + index * MAX_DATA_LEN) as u64,
+                bytes: chunk.to_vec(),
+            };
+            self.log.append(ino, &record)?;
+        }
+
+        let inode = self.get_mut(ino)?;
+        inode.data.resize(size, 0);
+        inode.data[offset..offset + bytes.len()].copy_from_slice(bytes);
+        let now = RealTimeCoarseClock::get().read_time();
+        inode.mtime = now;
+        inode.ctime = now;
+        self.write_inode_record(ino)
+    }
+
+    /// Updates the attributes of the inode, e.g., the mode and the owner.
+    pub(super) fn update(&mut self, ino: u64, f: impl FnOnce(&mut InodeData)) -> Result<()> {
+        let len = self.log.inode_record_len(&self.get(ino)?.name);
+        self.prepare(len, 0)?;
+
+        let inode = self.get_mut(ino)?;
+        f(inode);
+        inode.ctime = RealTimeCoarseClock::get().read_time();
+        self.write_inode_record(ino)
+    }
+}
@@ -0,0 +1,332 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#![expect(unused_variables)]
+
+use core::time::Duration;
+
+use super::fs::{FlashFs, InodeData, BLOCK_SIZE};
+use crate::{
+    fs::utils::{DirentVisitor, FileSystem, Inode, InodeMode, InodeType, Metadata, MknodType},
+    prelude::*,
+    process::{Gid, Uid},
+};
+
+/// An inode of the flash filesystem.
+///
+/// The inode is a handle of the inode that is cached by [`FlashFs`], so it cannot be used
+/// after being removed.
+pub(super) struct FlashInode {
+    ino: u64,
+    fs: Weak<FlashFs>,
+}
+
+impl FlashInode {
+    pub(super) fn new(ino: u64, fs: Weak<FlashFs>) -> Arc<Self> {
+        Arc::new(Self { ino, fs })
+    }
+
+    fn flash_fs(&self) -> Arc<FlashFs> {
+        self.fs.upgrade().unwrap()
+    }
+
+    /// Reads the attributes of the inode.
+    fn with_data<R>(&self, f: impl FnOnce(&InodeData) -> R) -> Result<R> {
+        let fs = self.flash_fs();
+        let state = fs.state();
+        Ok(f(state.get(self.ino)?))
+    }
+
+    /// Updates the attributes of the inode that are not persisted until the next write.
+    fn set_time(&self, f: impl FnOnce(&mut InodeData)) {
+        let fs = self.flash_fs();
+        let mut state = fs.state();
+        if let Some(inode) = state.inodes.get_mut(&self.ino) {
+            f(inode);
+        }
+    }
+
+    fn check_dir(&self) -> Result<()> {
+        if self.with_data(|inode| inode.type_)? != InodeType::Dir {
+            return_errno_with_message!(Errno::ENOTDIR, "the inode is not a directory");
+        }
+        Ok(())
+    }
+}
+
+impl Inode for FlashInode {
+    fn size(&self) -> usize {
+        self.with_data(|inode| inode.data.len()).unwrap_or(0)
+    }
+
+    fn resize(&self, new_size: usize) -> Result<()> {
+        match self.with_data(|inode| inode.type_)? {
+            InodeType::File => self.flash_fs().state().resize(self.ino, new_size),
+            InodeType::Dir => Err(Error::new(Errno::EISDIR)),
+            _ => Err(Error::new(Errno::EINVAL)),
+        }
+    }
+
+    fn metadata(&self) -> Metadata {
+        let fs = self.flash_fs();
+        let state = fs.state();
+        let Ok(inode) = state.get(self.ino) else {
+            let mut metadata = Metadata::new_file(self.ino, InodeMode::empty(), BLOCK_SIZE);
+            metadata.nlinks = 0;
+            return metadata;
+        };
+
+        let (size, nlinks) = match inode.type_ {
+            InodeType::Dir => {
+                let nr_subdirs = inode
+                    .children
+                    .values()
+                    .filter(|ino| state.inodes[*ino].type_ == InodeType::Dir)
+                    .count();
+                (inode.children.len() + 2, nr_subdirs + 2)
+            }
+            _ => (inode.data.len(), 1),
+        };
+        Metadata {
+            dev: 0,
+            ino: self.ino,
+            size,
+            blk_size: BLOCK_SIZE,
+            blocks: size.div_ceil(512),
+            atime: inode.atime,
+            mtime: inode.mtime,
+            ctime: inode.ctime,
+            btime: None,
+            type_: inode.type_,
+            mode: inode.mode,
+            nlinks,
+            uid: inode.uid,
+            gid: inode.gid,
+            rdev: 0,
+        }
+    }
+
+    fn ino(&self) -> u64 {
+        self.ino
+    }
+
+    fn type_(&self) -> InodeType {
+        self.with_data(|inode| inode.type_)
+            .unwrap_or(InodeType::File)
+    }
+
+    fn mode(&self) -> Result<InodeMode> {
+        self.with_data(|inode| inode.mode)
+    }
+
+    fn set_mode(&self, mode: InodeMode) -> Result<()> {
+        self.flash_fs()
+            .state()
+            .update(self.ino, |inode| inode.mode = mode)
+    }
+
+    fn owner(&self) -> Result<Uid> {
+        self.with_data(|inode| inode.uid)
+    }
+
+    fn set_owner(&self, uid: Uid) -> Result<()> {
+        self.flash_fs()
+            .state()
+            .update(self.ino, |inode| inode.uid = uid)
+    }
+
+    fn group(&self) -> Result<Gid> {
+        self.with_data(|inode| inode.gid)
+    }
+
+    fn set_group(&self, gid: Gid) -> Result<()> {
+        self.flash_fs()
+            .state()
+            .update(self.ino, |inode| inode.gid = gid)
+    }
+
+    fn atime(&self) -> Duration {
+        self.with_data(|inode| inode.atime).unwrap_or_default()
+    }
+
+    fn set_atime(&self, time: Duration) {
+        self.set_time(|inode| inode.atime = time);
+    }
+
+    fn mtime(&self) -> Duration {
+        self.with_data(|inode| inode.mtime).unwrap_or_default()
+    }
+
+    fn set_mtime(&self, time: Duration) {
+        self.set_time(|inode| inode.mtime = time);
+    }
+
+    fn ctime(&self) -> Duration {
+        self.with_data(|inode| inode.ctime).unwrap_or_default()
+    }
+
+    fn set_ctime(&self, time: Duration) {
+        self.set_time(|inode| inode.ctime = time);
+    }
+
+    fn read_at(&self, offset: usize, writer: &mut VmWriter) -> Result<usize> {
+        let fs = self.flash_fs();
+        let state = fs.state();
+        let inode = state.get(self.ino)?;
+        match inode.type_ {
+            InodeType::File => {}
+            InodeType::Dir => return_errno!(Errno::EISDIR),
+            _ => return_errno!(Errno::EINVAL),
+        }
+
+        let Some(bytes) = inode.data.get(offset..) else {
+            return Ok(0);
+        };
+        let len = writer.write_fallible(&mut bytes.into())?;
+        Ok(len)
+    }
+
+    fn read_direct_at(&self, offset: usize, writer: &mut VmWriter) -> Result<usize> {
+        self.read_at(offset, writer)
+    }
+
+    fn write_at(&self, offset: usize, reader: &mut VmReader) -> Result<usize> {
+        match self.with_data(|inode| inode.type_)? {
+            InodeType::File => {}
+            InodeType::Dir => return_errno!(Errno::EISDIR),
+            _ => return_errno!(Errno::EINVAL),
+        }
+
+        let mut bytes = vec![0u8; reader.remain()];
+        let len = reader.read_fallible(&mut bytes.as_mut_slice().into())?;
+        self.flash_fs()
+            .state()
+            .write(self.ino, offset, &bytes[..len])?;
+        Ok(len)
+    }
+
+    fn write_direct_at(&self, offset: usize, reader: &mut VmReader) -> Result<usize> {
+        self.write_at(offset, reader)
+    }
+
+    fn create(&self, name: &str, type_: InodeType, mode: InodeMode) -> Result<Arc<dyn Inode>> {
+        let inode = self
+            .flash_fs()
+            .state()
+            .create(self.ino, name, type_, mode)?;
+        Ok(inode)
+    }
+
+    fn mknod(&self, name: &str, mode: InodeMode, type_: MknodType) -> Result<Arc<dyn Inode>> {
+        return_errno_with_message!(Errno::EPERM, "the special files are not supported");
+    }
+
+    fn readdir_at(&self, offset: usize, visitor: &mut dyn DirentVisitor) -> Result<usize> {
+        let fs = self.flash_fs();
+        let state = fs.state();
+        let dir = state.get(self.ino)?;
+        if dir.type_ != InodeType::Dir {
+            return_errno_with_message!(Errno::ENOTDIR, "the inode is not a directory");
+        }
+
+        let try_readdir = |offset: &mut usize, visitor: &mut dyn DirentVisitor| -> Result<()> {
+            // Read the 2 special entries.
+            if *offset == 0 {
+                visitor.visit(".", self.ino, InodeType::Dir, *offset)?;
+                *offset += 1;
+            }
+            if *offset == 1 {
+                visitor.visit("..", dir.parent, InodeType::Dir, *offset)?;
+                *offset += 1;
+            }
+
+            // Read the normal entries.
+            for (idx, (name, ino)) in dir.children.iter().enumerate().skip(*offset - 2) {
+                visitor.visit(name, *ino, state.inodes[ino].type_, idx + 2)?;
+                *offset = idx + 3;
+            }
+            Ok(())
+        };
+
+        let mut iterate_offset = offset;
+        match try_readdir(&mut iterate_offset, visitor) {
+            Err(e) if offset == iterate_offset => Err(e),
+            _ => Ok(iterate_offset - offset),
+        }
+    }
+
+    fn link(&self, old: &Arc<dyn Inode>, name: &str) -> Result<()> {
+        self.check_dir()?;
+        return_errno_with_message!(Errno::EPERM, "the hard links are not supported");
+    }
+
+    fn unlink(&self, name: &str) -> Result<()> {
+        self.check_dir()?;
+        if name == "." || name == ".." {
+            return_errno_with_message!(Errno::EISDIR, "unlink . or ..");
+        }
+        self.flash_fs().state().remove(self.ino, name, false)
+    }
+
+    fn rmdir(&self, name: &str) -> Result<()> {
+        self.check_dir()?;
+        match name {
+            "." => return_errno_with_message!(Errno::EINVAL, "rmdir ."),
+            ".." => return_errno_with_message!(Errno::ENOTEMPTY, "rmdir .."),
+            _ => self.flash_fs().state().remove(self.ino, name, true),
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>> {
+        self.check_dir()?;
+        let fs = self.flash_fs();
+        let state = fs.state();
+        let inode = match name {
+            "." => state.get(self.ino)?.handle.clone(),
+            ".." => {
+                let parent = state.get(self.ino)?.parent;
+                state.get(parent)?.handle.clone()
+            }
+            name => state.lookup(self.ino, name)?,
+        };
+        Ok(inode)
+    }
+
+    fn rename(&self, old_name: &str, target: &Arc<dyn Inode>, new_name: &str) -> Result<()> {
+        if [old_name, new_name]
+            .iter()
+            .any(|name| *name == "." || *name == "..")
+        {
+            return_errno_with_message!(Errno::EISDIR, "rename . or ..");
+        }
+        let target = target
+            .downcast_ref::<FlashInode>()
+            .filter(|target| Weak::ptr_eq(&target.fs, &self.fs))
+            .ok_or_else(|| Error::with_message(Errno::EXDEV, "not the same filesystem"))?;
+        self.check_dir()?;
+
+        self.flash_fs()
+            .state()
+            .rename(self.ino, old_name, target.ino, new_name)
+    }
+
+    fn read_link(&self) -> Result<String> {
+        let target = self
+            .with_data(|inode| (inode.type_ == InodeType::SymLink).then(|| inode.data.clone()))?;
+        let Some(target) = target else {
+            return_errno_with_message!(Errno::EINVAL, "the inode is not a symbolic link");
+        };
+        String::from_utf8(target)
+            .map_err(|_| Error::with_message(Errno::EINVAL, "the target is not valid UTF-8"))
+    }
+
+    fn write_link(&self, target: &str) -> Result<()> {
+        if self.with_data(|inode| inode.type_)? != InodeType::SymLink {
+            return_errno_with_message!(Errno::EINVAL, "the inode is not a symbolic link");
+        }
+        self.flash_fs().state().set_link(self.ino, target)
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        self.flash_fs()
+    }
+}
@@ -0,0 +1,491 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The log of the records on the flash.
+//!
+//! Each erase block holds the records one after another from its start, and the rest of the
+//! block is erased (i.e., all `0xff`). A record consists of a header and its payload:
+//!
+//! | Offset | Size | Field                                       |
+//! | ------ | ---- | ------------------------------------------- |
+//! | 0      | 2    | The magic                                   |
+//! | 2      | 1    | The type                                    |
+//! | 3      | 1    | Reserved                                    |
+//! | 4      | 4    | The length of the payload                   |
+//! | 8      | 8    | The version, which orders the records       |
+//! | 16     | 8    | The inode number                            |
+//! | 24     | 4    | The CRC32C of the payload                   |
+//! | 28     | 4    | The CRC32C of the bytes 0..28 of the header |
+//!
+//! The records are aligned to the pages of the flash, or to 4 bytes if the flash can be
+//! written byte by byte. A record never crosses the boundary of the erase blocks.
+
+use core::time::Duration;
+
+use aster_mtd::{MtdDevice, MtdError};
+
+use crate::{
+    fs::{ext2::csum::crc32c, utils::InodeType},
+    prelude::*,
+};
+
+const RECORD_MAGIC: u16 = 0x1985;
+const RECORD_HEADER_LEN: usize = 32;
+
+/// The maximum number of the bytes in a data record.
+pub(super) const MAX_DATA_LEN: usize = 4096;
+
+/// The number of the erase blocks that are reserved for the garbage collection.
+pub(super) const RESERVED_BLOCKS: usize = 2;
+
+const TYPE_INODE: u8 = 1;
+const TYPE_DATA: u8 = 2;
+const TYPE_DELETE: u8 = 3;
+
+/// The length of the payload of an inode record without the name.
+const INODE_RECORD_LEN: usize = 54;
+/// The length of the payload of a data record without the data.
+const DATA_RECORD_LEN: usize = 8;
+
+impl From<MtdError> for Error {
+    fn from(err: MtdError) -> Self {
+        match err {
+            MtdError::InvalidArgs => Error::with_message(Errno::EINVAL, "invalid flash range"),
+            MtdError::ReadOnly => Error::with_message(Errno::EROFS, "the flash is read-only"),
+            MtdError::NotSupported => {
+                Error::with_message(Errno::EOPNOTSUPP, "the flash does not support it")
+            }
+            MtdError::BadBlock => Error::with_message(Errno::EIO, "bad erase block"),
+            MtdError::EccError => Error::with_message(Errno::EBADMSG, "uncorrectable bit flips"),
+            MtdError::Timeout => Error::with_message(Errno::ETIMEDOUT, "the flash timed out"),
+            MtdError::IoError => Error::with_message(Errno::EIO, "the flash failed"),
+        }
+    }
+}
+
+/// A record in the log.
+#[derive(Debug)]
+pub(super) enum Record {
+    /// The attributes and the location of an inode.
+    Inode(InodeRecord),
+    /// The data of a file (or the target of a symbolic link) at the offset.
+    Data { offset: u64, bytes: Vec<u8> },
+    /// The removal of an inode.
+    Delete,
+}
+
+/// The payload of an inode record.
+#[derive(Debug)]
+pub(super) struct InodeRecord {
+    pub(super) parent: u64,
+    pub(super) type_: InodeType,
+    pub(super) mode: u16,
+    pub(super) uid: u32,
+    pub(super) gid: u32,
+    pub(super) mtime: Duration,
+    pub(super) ctime: Duration,
+    pub(super) size: u64,
+    pub(super) name: String,
+}
+
+impl Record {
+    fn type_(&self) -> u8 {
+        match self {
+            Record::Inode(_) => TYPE_INODE,
+            Record::Data { .. } => TYPE_DATA,
+            Record::Delete => TYPE_DELETE,
+        }
+    }
+
+    fn payload_len(&self) -> usize {
+        match self {
+            Record::Inode(inode) => INODE_RECORD_LEN + inode.name.len(),
+            Record::Data { bytes, .. } => DATA_RECORD_LEN + bytes.len(),
+            Record::Delete => 0,
+        }
+    }
+
+    fn encode_payload(&self, buf: &mut Vec<u8>) {
+        match self {
+            Record::Inode(inode) => {
+                buf.extend_from_slice(&inode.parent.to_le_bytes());
+                buf.extend_from_slice(&(inode.type_ as u16).to_le_bytes());
+                buf.extend_from_slice(&inode.mode.to_le_bytes());
+                buf.extend_from_slice(&inode.uid.to_le_bytes());
+                buf.extend_from_slice(&inode.gid.to_le_bytes());
+                for time in [inode.mtime, inode.ctime] {
+                    buf.extend_from_slice(&time.as_secs().to_le_bytes());
+                    buf.extend_from_slice(&time.subsec_nanos().to_le_bytes());
+                }
+                buf.extend_from_slice(&inode.size.to_le_bytes());
+                buf.extend_from_slice(&(inode.name.len() as u16).to_le_bytes());
+                buf.extend_from_slice(inode.name.as_bytes());
+            }
+            Record::Data { offset, bytes } => {
+                buf.extend_from_slice(&offset.to_le_bytes());
+                buf.extend_from_slice(bytes);
+            }
+            Record::Delete => {}
+        }
+    }
+
+    fn decode(type_: u8, payload: &[u8]) -> Option<Self> {
+        let u16_at = |pos: usize| u16::from_le_bytes(payload[pos..pos + 2].try_into().unwrap());
+        let u32_at = |pos: usize| u32::from_le_bytes(payload[pos..pos + 4].try_into().unwrap());
+        let u64_at = |pos: usize| u64::from_le_bytes(payload[pos..pos + 8].try_into().unwrap());
+        let time_at = |pos: usize| Duration::new(u64_at(pos), u32_at(pos + 8).min(999_999_999));
+
+        match type_ {
+            TYPE_INODE => {
+                if payload.len() < INODE_RECORD_LEN {
+                    return None;
+                }
+                let name_len = u16_at(52) as usize;
+                let name = payload.get(INODE_RECORD_LEN..INODE_RECORD_LEN + name_len)?;
+                Some(Record::Inode(InodeRecord {
+                    parent: u64_at(0),
+                    type_: InodeType::try_from(u16_at(8)).ok()?,
+                    mode: u16_at(10),
+                    uid: u32_at(12),
+                    gid: u32_at(16),
+                    mtime: time_at(20),
+                    ctime: time_at(32),
+                    size: u64_at(44),
+                    name: String::from(core::str::from_utf8(name).ok()?),
+                }))
+            }
+            TYPE_DATA => {
+                if payload.len() < DATA_RECORD_LEN {
+                    return None;
+                }
+                Some(Record::Data {
+                    offset: u64_at(0),
+                    bytes: payload[DATA_RECORD_LEN..].to_vec(),
+                })
+            }
+            TYPE_DELETE => Some(Record::Delete),
+            _ => None,
+        }
+    }
+}
+
+/// A record that is found when scanning the flash.
+pub(super) struct ScannedRecord {
+    pub(super) version: u64,
+    pub(super) ino: u64,
+    pub(super) record: Record,
+}
+
+/// The log on an MTD device.
+pub(super) struct Log {
+    device: Arc<MtdDevice>,
+    block_size: usize,
+    align: usize,
+    blocks: Vec<Block>,
+    /// The block that the records are appended to
+    head: Option<usize>,
+    next_version: u64,
+}
+
+struct Block {
+    state: BlockState,
+    /// The offset of the free space in the block
+    used: usize,
+    /// The version of the newest record in the block
+    max_version: u64,
+    /// The inodes that have records in the block
+    inos: BTreeSet<u64>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum BlockState {
+    /// The block has no records. If it is found when mounting, it may have not been erased.
+    Free {
+        erased: bool,
+    },
+    Used,
+    Bad,
+}
+
+impl Log {
+    /// Scans the records on the device.
+    ///
+    /// The records are appended to a new block after the scan, since the free space of the
+    /// used blocks may be partially written before a power loss.
+    pub(super) fn scan(device: Arc<MtdDevice>) -> Result<(Self, Vec<ScannedRecord>)> {
+        let block_size = device.erase_size() as usize;
+        let align = (device.write_size() as usize).next_multiple_of(4);
+        let nr_blocks = (device.size() / block_size as u64) as usize;
+
+        let mut log = Self {
+            device,
+            block_size,
+            align,
+            blocks: Vec::with_capacity(nr_blocks),
+            head: None,
+            next_version: 1,
+        };
+        if log.block_size < 2 * log.record_len(DATA_RECORD_LEN + MAX_DATA_LEN) {
+            return_errno_with_message!(Errno::EINVAL, "the erase blocks are too small");
+        }
+
+        let mut records = Vec::new();
+        for index in 0..nr_blocks {
+            let block = log.scan_block(index, &mut records)?;
+            log.next_version = log.next_version.max(block.max_version + 1);
+            log.blocks.push(block);
+        }
+        if log.nr_good_blocks() < RESERVED_BLOCKS + 2 {
+            return_errno_with_message!(Errno::EINVAL, "the flash has too few erase blocks");
+        }
+        Ok((log, records))
+    }
+
+    fn scan_block(&self, index: usize, records: &mut Vec<ScannedRecord>) -> Result<Block> {
+        let mut block = Block {
+            state: BlockState::Free { erased: false },
+            used: 0,
+            max_version: 0,
+            inos: BTreeSet::new(),
+        };
+        let start = (index * self.block_size) as u64;
+        if self.device.is_bad_block(start) {
+            block.state = BlockState::Bad;
+            return Ok(block);
+        }
+
+        let mut header = [0u8; RECORD_HEADER_LEN];
+        let mut pos = 0;
+        while pos + RECORD_HEADER_LEN <= self.block_size {
+            match self.device.read(start + pos as u64, &mut header) {
+                Ok(()) => {}
+                // Ignore the rest of the block.
+                Err(MtdError::EccError) => break,
+                Err(err) => return Err(err.into()),
+            }
+            if header.iter().all(|byte| *byte == 0xff) {
+                break;
+            }
+
+            let magic = u16::from_le_bytes([header[0], header[1]]);
+            let len = u32::from_le_bytes(header[4..8].try_into().unwrap()) as usize;
+            let header_crc = u32::from_le_bytes(header[28..32].try_into().unwrap());
+            if magic != RECORD_MAGIC
+                || crc32c(!0, &header[..28]) != header_crc
+                || pos + RECORD_HEADER_LEN + len > self.block_size
+            {
+                break;
+            }
+
+            let mut payload = vec![0u8; len];
+            let payload_crc = u32::from_le_bytes(header[24..28].try_into().unwrap());
+            let is_valid = self
+                .device
+                .read(start + (pos + RECORD_HEADER_LEN) as u64, &mut payload)
+                .is_ok()
+                && crc32c(!0, &payload) == payload_crc;
+            // The length is trusted since the header is valid, so a corrupted record can be
+            // skipped.
+            if let Some(record) = is_valid
+                .then(|| Record::decode(header[2], &payload))
+                .flatten()
+            {
+                let version = u64::from_le_bytes(header[8..16].try_into().unwrap());
+                let ino = u64::from_le_bytes(header[16..24].try_into().unwrap());
+                block.max_version = block.max_version.max(version);
+                block.inos.insert(ino);
+                records.push(ScannedRecord {
+                    version,
+                    ino,
+                    record,
+                });
+            }
+            pos += self.record_len(len);
+        }
+
+        if pos > 0 {
+            block.state = BlockState::Used;
+            block.used = self.block_size;
+        }
+        Ok(block)
+    }
+
+    /// Returns the length of a record with the payload in the flash.
+    fn record_len(&self, payload_len: usize) -> usize {
+        (RECORD_HEADER_LEN + payload_len).next_multiple_of(self.align)
+    }
+
+    /// Returns the length of the record in the flash.
+    pub(super) fn len_of(&self, record: &Record) -> usize {
+        self.record_len(record.payload_len())
+    }
+
+    /// Returns the length of an inode record with the name in the flash.
+    pub(super) fn inode_record_len(&self, name: &str) -> usize {
+        self.record_len(INODE_RECORD_LEN + name.len())
+    }
+
+    /// Returns the length of the data records of the bytes in the flash.
+    pub(super) fn data_records_len(&self, len: usize) -> usize {
+        let nr_full = len / MAX_DATA_LEN;
+        let remain = len % MAX_DATA_LEN;
+        let mut total = nr_full * self.record_len(DATA_RECORD_LEN + MAX_DATA_LEN);
+        if remain > 0 {
+            total += self.record_len(DATA_RECORD_LEN + remain);
+        }
+        total
+    }
+
+    pub(super) fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    pub(super) fn nr_blocks(&self) -> usize {
+        self.blocks.len()
+    }
+
+    fn nr_good_blocks(&self) -> usize {
+        self.blocks
+            .iter()
+            .filter(|block| block.state != BlockState::Bad)
+            .count()
+    }
+
+    /// Returns the number of the bytes that the live records can occupy.
+    ///
+    /// Besides the reserved blocks, the end of each block may be wasted since the records do
+    /// not cross the blocks.
+    pub(super) fn capacity(&self) -> usize {
+        let usable_len = self.block_size - self.record_len(DATA_RECORD_LEN + MAX_DATA_LEN);
+        self.nr_good_blocks().saturating_sub(RESERVED_BLOCKS + 1) * usable_len
+    }
+
+    /// Returns the number of the bytes that can be appended without the garbage collection.
+    pub(super) fn free_len(&self) -> usize {
+        let nr_free = self
+            .blocks
+            .iter()
+            .filter(|block| matches!(block.state, BlockState::Free { .. }))
+            .count();
+        let head_free = self
+            .head
+            .map_or(0, |head| self.block_size - self.blocks[head].used);
+        nr_free * self.block_size + head_free
+    }
+
+    /// Returns the block to be collected, i.e., the used block with the oldest records.
+    pub(super) fn victim(&self) -> Option<usize> {
+        self.blocks
+            .iter()
+            .enumerate()
+            .filter(|(index, block)| block.state == BlockState::Used && Some(*index) != self.head)
+            .min_by_key(|(_, block)| block.max_version)
+            .map(|(index, _)| index)
+    }
+
+    /// Returns the inodes that have records in the block.
+    pub(super) fn inos_of(&self, index: usize) -> Vec<u64> {
+        self.blocks[index].inos.iter().copied().collect()
+    }
+
+    /// Returns whether the inode has records in the blocks other than the block.
+    pub(super) fn has_records_elsewhere(&self, ino: u64, index: usize) -> bool {
+        self.blocks
+            .iter()
+            .enumerate()
+            .any(|(i, block)| i != index && block.inos.contains(&ino))
+    }
+
+    /// Appends a record of the inode to the log.
+    pub(super) fn append(&mut self, ino: u64, record: &Record) -> Result<()> {
+        let len = self.len_of(record);
+        let head = match self.head {
+            Some(head) if self.blocks[head].used + len <= self.block_size => head,
+            _ => self.alloc_block()?,
+        };
+
+        let version = self.next_version;
+        let mut buf = Vec::with_capacity(len);
+        buf.extend_from_slice(&RECORD_MAGIC.to_le_bytes());
+        buf.push(record.type_());
+        buf.push(0);
+        buf.extend_from_slice(&(record.payload_len() as u32).to_le_bytes());
+        buf.extend_from_slice(&version.to_le_bytes());
+        buf.extend_from_slice(&ino.to_le_bytes());
+        buf.extend_from_slice(&[0u8; 8]);
+        record.encode_payload(&mut buf);
+        let payload_crc = crc32c(!0, &buf[RECORD_HEADER_LEN..]);
+        buf[24..28].copy_from_slice(&payload_crc.to_le_bytes());
+        let header_crc = crc32c(!0, &buf[..28]);
+        buf[28..32].copy_from_slice(&header_crc.to_le_bytes());
+        // The padding is left erased.
+        buf.resize(len, 0xff);
+
+        let block = &mut self.blocks[head];
+        let offset = (head * self.block_size + block.used) as u64;
+        block.inos.insert(ino);
+        block.max_version = version;
+        self.next_version += 1;
+        if let Err(err) = self.device.write(offset, &buf) {
+            // The rest of the block may be partially written.
+            block.used = self.block_size;
+            return Err(err.into());
+        }
+        block.used += len;
+        Ok(())
+    }
+
+    /// Allocates an erased block as the head.
+    fn alloc_block(&mut self) -> Result<usize> {
+        if let Some(head) = self.head.take() {
+            self.blocks[head].used = self.block_size;
+        }
+
+        loop {
+            // Prefer the erased blocks to avoid unnecessary erases.
+            let Some((index, erased)) = self
+                .blocks
+                .iter()
+                .enumerate()
+                .filter_map(|(index, block)| match block.state {
+                    BlockState::Free { erased } => Some((index, erased)),
+                    _ => None,
+                })
+                .max_by_key(|(_, erased)| *erased)
+            else {
+                return_errno_with_message!(Errno::ENOSPC, "no erased blocks");
+            };
+            if !erased && self.erase_block(index).is_err() {
+                continue;
+            }
+
+            let block = &mut self.blocks[index];
+            block.state = BlockState::Used;
+            block.used = 0;
+            self.head = Some(index);
+            return Ok(index);
+        }
+    }
+
+    /// Erases the block, whose records are no longer needed.
+    ///
+    /// If the block cannot be erased, it is marked as bad.
+    pub(super) fn erase_block(&mut self, index: usize) -> Result<()> {
+        let block = &mut self.blocks[index];
+        block.inos.clear();
+        block.used = 0;
+        block.max_version = 0;
+        let offset = (index * self.block_size) as u64;
+        if let Err(err) = self.device.erase(offset, self.block_size as u64) {
+            warn!(
+                "[FLASHFS]: mtd{}: cannot erase the block at {:#x}: {:?}",
+                self.device.index(),
+                offset,
+                err
+            );
+            block.state = BlockState::Bad;
+            return Err(err.into());
+        }
+        block.state = BlockState::Free { erased: true };
+        Ok(())
+    }
+}
@@ -0,0 +1,28 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! A log-structured filesystem on the raw flashes, i.e., the MTD devices.
+//!
+//! The filesystem keeps the configuration of the flash-only boards across the reboots.
+//! Like JFFS2, each change of a file is appended to the flash as a record, and the files are
+//! rebuilt by replaying the records when mounting. All the files are cached in memory, so
+//! the filesystem is meant for the small partitions.
+//!
+//! When the erased blocks run out, the garbage is collected from the block with the oldest
+//! records: the latest records of the inodes in the block are rewritten, and then the block
+//! is erased. The blocks are used in turn, which levels the wear naively.
+//!
+//! The filesystem is mounted with `mount -t flashfs mtdN /mnt` (or `/dev/mtdN`, or
+//! `mtd:<name>` with the name of the partition). An erased device is an empty filesystem.
+//!
+//! # Limitation
+//!
+//! 1. Hard links and the special files (e.g., the device files) are not supported.
+//! 2. A removed file cannot be accessed, even if it is still opened.
+//! 3. The access times, and the times that are set by `utimensat`, are persisted with the
+//!    next change of the file.
+
+mod fs;
+mod inode;
+mod log;
+
+pub use fs::FlashFs;
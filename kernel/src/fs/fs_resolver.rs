@@ -7,11 +7,11 @@ use ostd::task::Task;
 use super::{
     file_table::{get_file_fast, FileDesc},
     inode_handle::InodeHandle,
-    path::Dentry,
-    rootfs::root_mount,
+    path::{is_dotdot, Dentry, PerMountFlags},
+    rootfs::root_dentry,
     utils::{AccessMode, CreationFlags, InodeMode, InodeType, StatusFlags, PATH_MAX, SYMLINKS_MAX},
 };
-use crate::{prelude::*, process::posix_thread::AsThreadLocal};
+use crate::{prelude::*, process::posix_thread::AsThreadLocal, security::hooks};
 
 /// The file descriptor of the current working directory.
 pub const AT_FDCWD: FileDesc = -100;
@@ -27,8 +27,8 @@ impl FsResolver {
     /// Creates a new file system resolver.
     pub fn new() -> Self {
         Self {
-            root: Dentry::new_fs_root(root_mount().clone()),
-            cwd: Dentry::new_fs_root(root_mount().clone()),
+            root: root_dentry(),
+            cwd: root_dentry(),
         }
     }
 
@@ -54,11 +54,23 @@ impl FsResolver {
 
     /// Opens or creates a file inode handler.
     pub fn open(&self, path: &FsPath, flags: u32, mode: u16) -> Result<InodeHandle> {
+        self.open_with_resolve(path, flags, mode, ResolveFlags::empty())
+    }
+
+    /// Opens or creates a file inode handler, restricting the lookup with `resolve`.
+    pub fn open_with_resolve(
+        &self,
+        path: &FsPath,
+        flags: u32,
+        mode: u16,
+        resolve: ResolveFlags,
+    ) -> Result<InodeHandle> {
         let open_args = OpenArgs::from_flags_and_mode(flags, mode)?;
 
         let follow_tail_link = open_args.follow_tail_link();
         let stop_on_parent = false;
         let mut lookup_ctx = LookupCtx::new(follow_tail_link, stop_on_parent);
+        lookup_ctx.resolve = resolve;
 
         let lookup_res = self.lookup_inner(path, &mut lookup_ctx);
 
@@ -97,9 +109,32 @@ impl FsResolver {
                     return_errno_with_message!(Errno::ELOOP, "file is a symlink");
                 }
             }
+            InodeType::CharDevice | InodeType::BlockDevice => {
+                if target_dentry
+                    .mount_node()
+                    .flags()
+                    .contains(PerMountFlags::NODEV)
+                    && !open_args.status_flags.contains(StatusFlags::O_PATH)
+                {
+                    return_errno_with_message!(Errno::EACCES, "the mount disallows devices");
+                }
+            }
             _ => {}
         }
 
+        // The special files are not stored in the file system, so they are writable
+        // even if the mount is read-only.
+        let is_stored = matches!(
+            inode_type,
+            InodeType::File | InodeType::Dir | InodeType::SymLink
+        );
+        if is_stored
+            && (open_args.access_mode.is_writable()
+                || creation_flags.contains(CreationFlags::O_TRUNC))
+        {
+            target_dentry.check_mount_writable()?;
+        }
+
         if creation_flags.contains(CreationFlags::O_CREAT)
             && creation_flags.contains(CreationFlags::O_EXCL)
         {
@@ -112,6 +147,10 @@ impl FsResolver {
             );
         }
 
+        if !open_args.status_flags.contains(StatusFlags::O_PATH) {
+            hooks::file_open(&target_dentry, open_args.access_mode)?;
+        }
+
         if creation_flags.contains(CreationFlags::O_TRUNC) {
             target_dentry.resize(0)?;
         }
@@ -140,6 +179,7 @@ impl FsResolver {
         let tail_file_name = lookup_ctx.tail_file_name().unwrap();
         let new_dentry =
             parent.new_fs_child(&tail_file_name, InodeType::File, open_args.inode_mode)?;
+        hooks::file_open(&new_dentry, open_args.access_mode)?;
         // Don't check access mode for newly created file
         InodeHandle::new_unchecked_access(new_dentry, open_args.access_mode, open_args.status_flags)
     }
@@ -160,6 +200,11 @@ impl FsResolver {
 
     fn lookup_inner(&self, path: &FsPath, lookup_ctx: &mut LookupCtx) -> Result<Dentry> {
         let dentry = match path.inner {
+            FsPathInner::Absolute(_)
+                if lookup_ctx.resolve.contains(ResolveFlags::RESOLVE_BENEATH) =>
+            {
+                return_errno_with_message!(Errno::EXDEV, "absolute path escapes the directory");
+            }
             FsPathInner::Absolute(path) => {
                 self.lookup_from_parent(&self.root, path.trim_start_matches('/'), lookup_ctx)?
             }
@@ -195,6 +240,8 @@ impl FsResolver {
     /// If `follow_tail_link` is true and the trailing component is a symlink,
     /// it will be followed.
     /// Symlinks in earlier components of the path will always be followed.
+    ///
+    /// The lookup is scoped to `parent` by `RESOLVE_BENEATH` and `RESOLVE_IN_ROOT`.
     #[expect(clippy::redundant_closure)]
     fn lookup_from_parent(
         &self,
@@ -218,6 +265,9 @@ impl FsResolver {
 
         // Initialize the first dentry and the relative path
         let (mut dentry, mut relative_path) = (parent.clone(), relative_path);
+        let resolve = lookup_ctx.resolve;
+        let is_scoped =
+            resolve.intersects(ResolveFlags::RESOLVE_BENEATH | ResolveFlags::RESOLVE_IN_ROOT);
 
         while !relative_path.is_empty() {
             let (next_name, path_remain, must_be_dir) =
@@ -235,6 +285,15 @@ impl FsResolver {
                 return Ok(dentry);
             }
 
+            if is_scoped && is_dotdot(next_name) && is_same_dentry(&dentry, parent) {
+                if resolve.contains(ResolveFlags::RESOLVE_BENEATH) {
+                    return_errno_with_message!(Errno::EXDEV, "\"..\" escapes the directory");
+                }
+                // With `RESOLVE_IN_ROOT`, the parent of the root is the root itself.
+                relative_path = path_remain;
+                continue;
+            }
+
             let next_dentry = match dentry.lookup(next_name) {
                 Ok(dentry) => dentry,
                 Err(e) => {
@@ -246,10 +305,28 @@ impl FsResolver {
                     return Err(e);
                 }
             };
+            if resolve.contains(ResolveFlags::RESOLVE_NO_XDEV)
+                && !Arc::ptr_eq(next_dentry.mount_node(), parent.mount_node())
+            {
+                return_errno_with_message!(Errno::EXDEV, "the lookup crosses a mount point");
+            }
             let next_type = next_dentry.type_();
 
             // If next inode is a symlink, follow symlinks at most `SYMLINKS_MAX` times.
             if next_type == InodeType::SymLink && (follow_tail_link || !next_is_tail) {
+                if resolve.contains(ResolveFlags::RESOLVE_NO_SYMLINKS)
+                    || next_dentry
+                        .mount_node()
+                        .flags()
+                        .contains(PerMountFlags::NOSYMFOLLOW)
+                {
+                    return_errno_with_message!(Errno::ELOOP, "symlinks are not allowed");
+                }
+                if (is_scoped || resolve.contains(ResolveFlags::RESOLVE_NO_MAGICLINKS))
+                    && next_dentry.inode().is_magic_link()
+                {
+                    return_errno_with_message!(Errno::ELOOP, "magic links are not allowed");
+                }
                 if follows >= SYMLINKS_MAX {
                     return_errno_with_message!(Errno::ELOOP, "too many symlinks");
                 }
@@ -269,7 +346,22 @@ impl FsResolver {
 
                 // Change the dentry and relative path according to symlink
                 if link_path_remain.starts_with('/') {
-                    dentry = self.root.clone();
+                    if resolve.contains(ResolveFlags::RESOLVE_BENEATH) {
+                        return_errno_with_message!(Errno::EXDEV, "symlink escapes the directory");
+                    }
+                    dentry = if resolve.contains(ResolveFlags::RESOLVE_IN_ROOT) {
+                        parent.clone()
+                    } else {
+                        self.root.clone()
+                    };
+                    if resolve.contains(ResolveFlags::RESOLVE_NO_XDEV)
+                        && !Arc::ptr_eq(dentry.mount_node(), parent.mount_node())
+                    {
+                        return_errno_with_message!(
+                            Errno::EXDEV,
+                            "the lookup crosses a mount point"
+                        );
+                    }
                 }
                 let link_path = link_path_opt.get_or_insert_with(|| String::new());
                 link_path.clear();
@@ -355,11 +447,35 @@ impl Default for FsResolver {
     }
 }
 
+/// Returns whether the two dentries refer to the same location.
+fn is_same_dentry(this: &Dentry, other: &Dentry) -> bool {
+    Arc::ptr_eq(this.mount_node(), other.mount_node()) && this.key() == other.key()
+}
+
+bitflags! {
+    /// The flags of `openat2` that restrict how a path is resolved.
+    pub struct ResolveFlags: u64 {
+        /// Do not cross mount points.
+        const RESOLVE_NO_XDEV = 0x01;
+        /// Do not follow magic links, e.g., `/proc/[pid]/fd/N`.
+        const RESOLVE_NO_MAGICLINKS = 0x02;
+        /// Do not follow any symlinks.
+        const RESOLVE_NO_SYMLINKS = 0x04;
+        /// Do not escape the starting directory.
+        const RESOLVE_BENEATH = 0x08;
+        /// Treat the starting directory as the root directory.
+        const RESOLVE_IN_ROOT = 0x10;
+        /// Only resolve with the cached dentries.
+        const RESOLVE_CACHED = 0x20;
+    }
+}
+
 /// Context information describing one lookup operation.
 #[derive(Debug)]
 struct LookupCtx {
     follow_tail_link: bool,
     stop_on_parent: bool,
+    resolve: ResolveFlags,
     // (file_name, file_is_dir)
     tail_file: Option<(String, bool)>,
     parent: Option<Dentry>,
@@ -370,6 +486,7 @@ impl LookupCtx {
         Self {
             follow_tail_link,
             stop_on_parent,
+            resolve: ResolveFlags::empty(),
             tail_file: None,
             parent: None,
         }
@@ -0,0 +1,511 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#![expect(unused_variables)]
+
+//! The hugetlbfs filesystem.
+//!
+//! The regular files in the filesystem are backed by the huge pages in the pools (see
+//! [`crate::vm::hugetlb`]), which are usually mapped into the memory with `mmap`. Like Linux,
+//! the files cannot be written with `write`, and mapping a file reserves the huge pages of the
+//! mapped range.
+//!
+//! The filesystem only has the root directory for now, in which the files are created.
+
+use core::{
+    ops::Range,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use align_ext::AlignExt;
+use aster_rights::Full;
+use ostd::mm::VmIo;
+
+use crate::{
+    fs::{
+        ramfs::parse_number,
+        utils::{
+            DirentVisitor, FileSystem, FsFlags, Inode, InodeMode, InodeType, Metadata, SuperBlock,
+            NAME_MAX,
+        },
+    },
+    prelude::*,
+    process::{posix_thread::AsPosixThread, Gid, Uid},
+    time::clocks::RealTimeCoarseClock,
+    vm::{
+        hugetlb::{self, HugePagePager, HugePagePool, HugePageSize},
+        vmo::Vmo,
+    },
+};
+
+const HUGETLBFS_MAGIC: u64 = 0x9584_58f6;
+const ROOT_INO: u64 = 1;
+
+/// The mount options of hugetlbfs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HugetlbfsMountOptions {
+    /// The size of the huge pages of the files.
+    pub page_size: HugePageSize,
+    /// The permission bits of the root directory.
+    pub mode: InodeMode,
+    pub uid: Uid,
+    pub gid: Gid,
+}
+
+impl HugetlbfsMountOptions {
+    /// Parses the options like `pagesize=1G,mode=1770`.
+    pub fn parse(options: &str) -> Result<Self> {
+        let mut mount_options = Self {
+            page_size: HugePageSize::DEFAULT,
+            mode: InodeMode::from_bits_truncate(0o755),
+            uid: Uid::new_root(),
+            gid: Gid::new_root(),
+        };
+
+        for option in options.split(',').filter(|option| !option.is_empty()) {
+            let (key, value) = option.split_once('=').unwrap_or((option, ""));
+            match key {
+                "pagesize" => {
+                    mount_options.page_size = HugePageSize::from_bytes(parse_number(value)?)
+                        .ok_or_else(|| {
+                            Error::with_message(Errno::EINVAL, "unsupported huge page size")
+                        })?;
+                }
+                "mode" => {
+                    let mode = u16::from_str_radix(value, 8)
+                        .map_err(|_| Error::with_message(Errno::EINVAL, "invalid mode"))?;
+                    mount_options.mode = InodeMode::from_bits_truncate(mode);
+                }
+                "uid" => mount_options.uid = Uid::new(parse_id(value)?),
+                "gid" => mount_options.gid = Gid::new(parse_id(value)?),
+                _ => {
+                    warn!("unsupported hugetlbfs mount option: {}", key);
+                    return_errno_with_message!(Errno::EINVAL, "unsupported mount option");
+                }
+            }
+        }
+
+        Ok(mount_options)
+    }
+}
+
+fn parse_id(s: &str) -> Result<u32> {
+    s.parse::<u32>()
+        .map_err(|_| Error::with_message(Errno::EINVAL, "invalid id"))
+}
+
+/// The hugetlbfs filesystem.
+pub struct HugetlbFs {
+    page_size: HugePageSize,
+    root: Arc<RootInode>,
+    next_ino: AtomicU64,
+}
+
+impl HugetlbFs {
+    pub fn new(options: HugetlbfsMountOptions) -> Arc<Self> {
+        Arc::new_cyclic(|weak_self| {
+            let mut metadata = Metadata::new_dir(ROOT_INO, options.mode, options.page_size.bytes());
+            metadata.uid = options.uid;
+            metadata.gid = options.gid;
+
+            Self {
+                page_size: options.page_size,
+                root: Arc::new(RootInode {
+                    files: RwLock::new(BTreeMap::new()),
+                    metadata: RwLock::new(metadata),
+                    fs: weak_self.clone(),
+                }),
+                next_ino: AtomicU64::new(ROOT_INO + 1),
+            }
+        })
+    }
+}
+
+impl FileSystem for HugetlbFs {
+    fn sync(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn root_inode(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+
+    fn sb(&self) -> SuperBlock {
+        let stats = HugePagePool::get(self.page_size).stats();
+        let mut sb = SuperBlock::new(HUGETLBFS_MAGIC, self.page_size.bytes(), NAME_MAX);
+        sb.blocks = stats.nr_total;
+        sb.bfree = stats.nr_free;
+        sb.bavail = stats.nr_free;
+        sb
+    }
+
+    fn flags(&self) -> FsFlags {
+        FsFlags::DENTRY_UNEVICTABLE
+    }
+}
+
+struct RootInode {
+    files: RwLock<BTreeMap<String, Arc<HugetlbInode>>>,
+    metadata: RwLock<Metadata>,
+    fs: Weak<HugetlbFs>,
+}
+
+impl RootInode {
+    fn touch(&self) {
+        let now = RealTimeCoarseClock::get().read_time();
+        let mut metadata = self.metadata.write();
+        metadata.mtime = now;
+        metadata.ctime = now;
+    }
+}
+
+impl Inode for RootInode {
+    fn size(&self) -> usize {
+        self.metadata.read().size
+    }
+
+    fn resize(&self, new_size: usize) -> Result<()> {
+        Err(Error::new(Errno::EISDIR))
+    }
+
+    fn metadata(&self) -> Metadata {
+        *self.metadata.read()
+    }
+
+    fn ino(&self) -> u64 {
+        ROOT_INO
+    }
+
+    fn type_(&self) -> InodeType {
+        InodeType::Dir
+    }
+
+    fn mode(&self) -> Result<InodeMode> {
+        Ok(self.metadata.read().mode)
+    }
+
+    fn set_mode(&self, mode: InodeMode) -> Result<()> {
+        self.metadata.write().mode = mode;
+        Ok(())
+    }
+
+    fn owner(&self) -> Result<Uid> {
+        Ok(self.metadata.read().uid)
+    }
+
+    fn set_owner(&self, uid: Uid) -> Result<()> {
+        self.metadata.write().uid = uid;
+        Ok(())
+    }
+
+    fn group(&self) -> Result<Gid> {
+        Ok(self.metadata.read().gid)
+    }
+
+    fn set_group(&self, gid: Gid) -> Result<()> {
+        self.metadata.write().gid = gid;
+        Ok(())
+    }
+
+    fn atime(&self) -> Duration {
+        self.metadata.read().atime
+    }
+
+    fn set_atime(&self, time: Duration) {
+        self.metadata.write().atime = time;
+    }
+
+    fn mtime(&self) -> Duration {
+        self.metadata.read().mtime
+    }
+
+    fn set_mtime(&self, time: Duration) {
+        self.metadata.write().mtime = time;
+    }
+
+    fn ctime(&self) -> Duration {
+        self.metadata.read().ctime
+    }
+
+    fn set_ctime(&self, time: Duration) {
+        self.metadata.write().ctime = time;
+    }
+
+    fn create(&self, name: &str, type_: InodeType, mode: InodeMode) -> Result<Arc<dyn Inode>> {
+        if type_ != InodeType::File {
+            return_errno_with_message!(Errno::EPERM, "only regular files can be created");
+        }
+        if name.len() > NAME_MAX {
+            return_errno_with_message!(Errno::ENAMETOOLONG, "the name is too long");
+        }
+
+        let mut files = self.files.write();
+        if files.contains_key(name) {
+            return_errno_with_message!(Errno::EEXIST, "the file exists");
+        }
+
+        let fs = self.fs.upgrade().unwrap();
+        let credentials = current_thread!().as_posix_thread().unwrap().credentials();
+        let inode = HugetlbInode::new(
+            fs.next_ino.fetch_add(1, Ordering::Relaxed),
+            fs.page_size,
+            mode,
+            credentials.fsuid(),
+            credentials.fsgid(),
+            self.fs.clone(),
+        )?;
+        files.insert(name.to_string(), inode.clone());
+        drop(files);
+
+        self.touch();
+        Ok(inode)
+    }
+
+    fn readdir_at(&self, offset: usize, visitor: &mut dyn DirentVisitor) -> Result<usize> {
+        let try_readdir = |offset: &mut usize, visitor: &mut dyn DirentVisitor| -> Result<()> {
+            // Read the 2 special entries.
+            if *offset == 0 {
+                visitor.visit(".", ROOT_INO, InodeType::Dir, *offset)?;
+                *offset += 1;
+            }
+            if *offset == 1 {
+                visitor.visit("..", ROOT_INO, InodeType::Dir, *offset)?;
+                *offset += 1;
+            }
+
+            // Read the files.
+            let files = self.files.read();
+            for (idx, (name, inode)) in files.iter().enumerate().skip(*offset - 2) {
+                visitor.visit(name, inode.ino(), InodeType::File, idx + 2)?;
+                *offset = idx + 3;
+            }
+            Ok(())
+        };
+
+        let mut iterate_offset = offset;
+        match try_readdir(&mut iterate_offset, visitor) {
+            Err(e) if offset == iterate_offset => Err(e),
+            _ => Ok(iterate_offset - offset),
+        }
+    }
+
+    fn link(&self, old: &Arc<dyn Inode>, name: &str) -> Result<()> {
+        Err(Error::new(Errno::EPERM))
+    }
+
+    fn unlink(&self, name: &str) -> Result<()> {
+        if self.files.write().remove(name).is_none() {
+            return_errno_with_message!(Errno::ENOENT, "the file does not exist");
+        }
+        self.touch();
+        Ok(())
+    }
+
+    fn rmdir(&self, name: &str) -> Result<()> {
+        Err(Error::new(Errno::ENOTDIR))
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>> {
+        let inode: Arc<dyn Inode> = match name {
+            "." | ".." => self.fs().root_inode(),
+            name => self
+                .files
+                .read()
+                .get(name)
+                .cloned()
+                .ok_or_else(|| Error::with_message(Errno::ENOENT, "the file does not exist"))?,
+        };
+        Ok(inode)
+    }
+
+    fn rename(&self, old_name: &str, target: &Arc<dyn Inode>, new_name: &str) -> Result<()> {
+        if !target
+            .downcast_ref::<RootInode>()
+            .is_some_and(|target| core::ptr::eq(target, self))
+        {
+            return_errno_with_message!(Errno::EXDEV, "the target is not in the filesystem");
+        }
+        if new_name.len() > NAME_MAX {
+            return_errno_with_message!(Errno::ENAMETOOLONG, "the name is too long");
+        }
+
+        let mut files = self.files.write();
+        let inode = files
+            .remove(old_name)
+            .ok_or_else(|| Error::with_message(Errno::ENOENT, "the file does not exist"))?;
+        files.insert(new_name.to_string(), inode);
+        drop(files);
+
+        self.touch();
+        Ok(())
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        self.fs.upgrade().unwrap()
+    }
+}
+
+/// A regular file in hugetlbfs.
+pub struct HugetlbInode {
+    vmo: Vmo<Full>,
+    pager: Arc<HugePagePager>,
+    metadata: RwLock<Metadata>,
+    fs: Weak<HugetlbFs>,
+}
+
+impl HugetlbInode {
+    fn new(
+        ino: u64,
+        page_size: HugePageSize,
+        mode: InodeMode,
+        uid: Uid,
+        gid: Gid,
+        fs: Weak<HugetlbFs>,
+    ) -> Result<Arc<Self>> {
+        let (vmo, pager) = hugetlb::alloc_vmo(page_size, 0, false)?;
+
+        let mut metadata = Metadata::new_file(ino, mode, page_size.bytes());
+        metadata.uid = uid;
+        metadata.gid = gid;
+
+        Ok(Arc::new(Self {
+            vmo,
+            pager,
+            metadata: RwLock::new(metadata),
+            fs,
+        }))
+    }
+
+    /// Returns the size of the huge pages of the file.
+    pub fn page_size(&self) -> HugePageSize {
+        self.pager.page_size()
+    }
+
+    /// Prepares the range (in bytes) of the file to be mapped.
+    ///
+    /// The huge pages in the range are reserved unless `reserve` is false (i.e.,
+    /// `MAP_NORESERVE`). Like Linux, a writable mapping extends the file to cover the range.
+    pub fn prepare_mmap(&self, range: &Range<usize>, reserve: bool, writable: bool) -> Result<()> {
+        if range.start % self.page_size().bytes() != 0 {
+            return_errno_with_message!(Errno::EINVAL, "the offset is not aligned to huge pages");
+        }
+
+        let end = range.end.align_up(self.page_size().bytes());
+        if writable && end > self.size() {
+            self.resize(end)?;
+        }
+        if reserve {
+            self.pager.reserve(&(range.start..end))?;
+        }
+        Ok(())
+    }
+}
+
+impl Inode for HugetlbInode {
+    fn size(&self) -> usize {
+        self.metadata.read().size
+    }
+
+    /// Resizes the file, which is rounded up to the size of the huge pages.
+    fn resize(&self, new_size: usize) -> Result<()> {
+        let new_size = new_size.align_up(self.page_size().bytes());
+
+        let mut metadata = self.metadata.write();
+        self.vmo.resize(new_size)?;
+        self.pager.truncate(new_size);
+        metadata.size = new_size;
+        metadata.blocks = new_size / self.page_size().bytes();
+        Ok(())
+    }
+
+    fn metadata(&self) -> Metadata {
+        *self.metadata.read()
+    }
+
+    fn ino(&self) -> u64 {
+        self.metadata.read().ino
+    }
+
+    fn type_(&self) -> InodeType {
+        InodeType::File
+    }
+
+    fn mode(&self) -> Result<InodeMode> {
+        Ok(self.metadata.read().mode)
+    }
+
+    fn set_mode(&self, mode: InodeMode) -> Result<()> {
+        self.metadata.write().mode = mode;
+        Ok(())
+    }
+
+    fn owner(&self) -> Result<Uid> {
+        Ok(self.metadata.read().uid)
+    }
+
+    fn set_owner(&self, uid: Uid) -> Result<()> {
+        self.metadata.write().uid = uid;
+        Ok(())
+    }
+
+    fn group(&self) -> Result<Gid> {
+        Ok(self.metadata.read().gid)
+    }
+
+    fn set_group(&self, gid: Gid) -> Result<()> {
+        self.metadata.write().gid = gid;
+        Ok(())
+    }
+
+    fn atime(&self) -> Duration {
+        self.metadata.read().atime
+    }
+
+    fn set_atime(&self, time: Duration) {
+        self.metadata.write().atime = time;
+    }
+
+    fn mtime(&self) -> Duration {
+        self.metadata.read().mtime
+    }
+
+    fn set_mtime(&self, time: Duration) {
+        self.metadata.write().mtime = time;
+    }
+
+    fn ctime(&self) -> Duration {
+        self.metadata.read().ctime
+    }
+
+    fn set_ctime(&self, time: Duration) {
+        self.metadata.write().ctime = time;
+    }
+
+    fn page_cache(&self) -> Option<Vmo<Full>> {
+        Some(self.vmo.dup())
+    }
+
+    fn read_at(&self, offset: usize, writer: &mut VmWriter) -> Result<usize> {
+        let size = self.size();
+        let start = size.min(offset);
+        let end = size.min(offset + writer.avail());
+        self.vmo.read(start, writer)?;
+        Ok(end - start)
+    }
+
+    fn read_direct_at(&self, offset: usize, writer: &mut VmWriter) -> Result<usize> {
+        self.read_at(offset, writer)
+    }
+
+    fn write_at(&self, offset: usize, reader: &mut VmReader) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "hugetlbfs files cannot be written");
+    }
+
+    fn write_direct_at(&self, offset: usize, reader: &mut VmReader) -> Result<usize> {
+        self.write_at(offset, reader)
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        self.fs.upgrade().unwrap()
+    }
+}
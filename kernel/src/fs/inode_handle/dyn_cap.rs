@@ -22,11 +22,15 @@ impl InodeHandle<Rights> {
         if inode.type_() == InodeType::Dir && access_mode.is_writable() {
             return_errno_with_message!(Errno::EISDIR, "directory cannot open to write");
         }
+        // This also loads the fs-verity state, so that the data can be verified on reads.
+        if inode.verity()?.is_some() && access_mode.is_writable() {
+            return_errno_with_message!(Errno::EPERM, "the file is protected by fs-verity");
+        }
 
         let file_io = if let Some(device) = inode.as_device() {
             device.open()?
         } else {
-            None
+            inode.open()?
         };
 
         let inner = Arc::new(InodeHandle_ {
@@ -77,6 +81,7 @@ impl Pollable for InodeHandle<Rights> {
 #[inherit_methods(from = "self.0")]
 impl FileLike for InodeHandle<Rights> {
     fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32>;
+    fn ioctl_raw(&self, cmd: u32, arg: usize) -> Result<i32>;
     fn status_flags(&self) -> StatusFlags;
     fn access_mode(&self) -> AccessMode;
     fn metadata(&self) -> Metadata;
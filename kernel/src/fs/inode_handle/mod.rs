@@ -18,15 +18,16 @@ use crate::{
         file_handle::FileLike,
         path::Dentry,
         utils::{
-            AccessMode, DirentVisitor, FallocMode, FileRange, FlockItem, FlockList, InodeMode,
-            InodeType, IoctlCmd, Metadata, RangeLockItem, RangeLockItemBuilder, RangeLockList,
-            RangeLockType, SeekFrom, StatusFlags, OFFSET_MAX,
+            ioctl_enable_verity, ioctl_measure_verity, ioctl_read_verity_metadata, AccessMode,
+            DirentVisitor, FallocMode, FileRange, FlockItem, FlockList, InodeMode, InodeType,
+            IoctlCmd, Metadata, RangeLockItem, RangeLockItemBuilder, RangeLockList, RangeLockType,
+            SeekFrom, StatusFlags, OFFSET_MAX,
         },
     },
     prelude::*,
     process::{
-        signal::{PollHandle, Pollable},
-        Gid, Uid,
+        signal::{constants::SIGXFSZ, signals::kernel::KernelSignal, PollHandle, Pollable},
+        Gid, Process, ResourceType, Uid,
     },
 };
 
@@ -47,7 +48,7 @@ struct InodeHandle_ {
 impl InodeHandle_ {
     pub fn read(&self, writer: &mut VmWriter) -> Result<usize> {
         if let Some(ref file_io) = self.file_io {
-            return file_io.read(writer);
+            return file_io.read_with_flags(writer, self.status_flags());
         }
 
         if !self.dentry.inode().is_seekable() {
@@ -64,7 +65,7 @@ impl InodeHandle_ {
 
     pub fn write(&self, reader: &mut VmReader) -> Result<usize> {
         if let Some(ref file_io) = self.file_io {
-            return file_io.write(reader);
+            return file_io.write_with_flags(reader, self.status_flags());
         }
 
         if !self.dentry.inode().is_seekable() {
@@ -84,8 +85,8 @@ impl InodeHandle_ {
     }
 
     pub fn read_at(&self, offset: usize, writer: &mut VmWriter) -> Result<usize> {
-        if let Some(ref file_io) = self.file_io {
-            todo!("support read_at for FileIo");
+        if self.file_io.is_some() {
+            return_errno_with_message!(Errno::ESPIPE, "the file does not support read_at");
         }
 
         if self.status_flags().contains(StatusFlags::O_DIRECT) {
@@ -96,8 +97,8 @@ impl InodeHandle_ {
     }
 
     pub fn write_at(&self, mut offset: usize, reader: &mut VmReader) -> Result<usize> {
-        if let Some(ref file_io) = self.file_io {
-            todo!("support write_at for FileIo");
+        if self.file_io.is_some() {
+            return_errno_with_message!(Errno::ESPIPE, "the file does not support write_at");
         }
 
         let status_flags = self.status_flags();
@@ -105,6 +106,7 @@ impl InodeHandle_ {
             // If the file has the O_APPEND flag, the offset is ignored
             offset = self.dentry.size();
         }
+        self.check_file_size_limit(offset, reader)?;
 
         if status_flags.contains(StatusFlags::O_DIRECT) {
             self.dentry.inode().write_direct_at(offset, reader)
@@ -113,6 +115,32 @@ impl InodeHandle_ {
         }
     }
 
+    /// Checks the write at `offset` against the `RLIMIT_FSIZE` of the current process.
+    ///
+    /// The data beyond the limit are not written. If nothing can be written, the process gets
+    /// a `SIGXFSZ` signal and this method fails with `EFBIG`.
+    fn check_file_size_limit(&self, offset: usize, reader: &mut VmReader) -> Result<()> {
+        if self.dentry.type_() != InodeType::File || reader.remain() == 0 {
+            return Ok(());
+        }
+        let Some(process) = Process::current() else {
+            return Ok(());
+        };
+
+        let max_file_size = process
+            .resource_limits()
+            .get_rlimit(ResourceType::RLIMIT_FSIZE)
+            .get_cur()
+            .try_into()
+            .unwrap_or(usize::MAX);
+        if offset >= max_file_size {
+            process.enqueue_signal(KernelSignal::new(SIGXFSZ));
+            return_errno_with_message!(Errno::EFBIG, "the file size exceeds the limit");
+        }
+        reader.limit(max_file_size - offset);
+        Ok(())
+    }
+
     pub fn read_to_end(&self, buf: &mut Vec<u8>) -> Result<usize> {
         if self.file_io.is_some() {
             return_errno_with_message!(Errno::EINVAL, "file io does not support read to end");
@@ -127,6 +155,12 @@ impl InodeHandle_ {
     }
 
     pub fn seek(&self, pos: SeekFrom) -> Result<usize> {
+        if let Some(ref file_io) = self.file_io {
+            if let Some(res) = file_io.seek(pos) {
+                return res;
+            }
+        }
+
         let mut offset = self.offset.lock();
         let new_offset: isize = match pos {
             SeekFrom::Start(off /* as usize */) => {
@@ -225,7 +259,29 @@ impl InodeHandle_ {
             return file_io.ioctl(cmd, arg);
         }
 
-        self.dentry.inode().ioctl(cmd, arg)
+        let inode = self.dentry.inode();
+        match cmd {
+            IoctlCmd::FS_IOC_ENABLE_VERITY => {
+                ioctl_enable_verity(inode.as_ref(), self.access_mode, arg)?;
+                Ok(0)
+            }
+            IoctlCmd::FS_IOC_MEASURE_VERITY => {
+                ioctl_measure_verity(inode.as_ref(), arg)?;
+                Ok(0)
+            }
+            IoctlCmd::FS_IOC_READ_VERITY_METADATA => {
+                Ok(ioctl_read_verity_metadata(inode.as_ref(), arg)? as i32)
+            }
+            _ => inode.ioctl(cmd, arg),
+        }
+    }
+
+    fn ioctl_raw(&self, cmd: u32, arg: usize) -> Result<i32> {
+        if let Some(ref file_io) = self.file_io {
+            return file_io.ioctl_raw(cmd, arg);
+        }
+
+        return_errno_with_message!(Errno::EINVAL, "the ioctl command is unknown");
     }
 
     fn test_range_lock(&self, lock: RangeLockItem) -> Result<RangeLockItem> {
@@ -396,7 +452,36 @@ pub trait FileIo: Pollable + Send + Sync + 'static {
 
     fn write(&self, reader: &mut VmReader) -> Result<usize>;
 
+    /// Reads from the file, taking the status flags of the file handle into account.
+    ///
+    /// Files that support nonblocking I/O should override this method to check
+    /// [`StatusFlags::O_NONBLOCK`]. By default, the status flags are ignored.
+    fn read_with_flags(&self, writer: &mut VmWriter, status_flags: StatusFlags) -> Result<usize> {
+        self.read(writer)
+    }
+
+    /// Writes to the file, taking the status flags of the file handle into account.
+    ///
+    /// See [`FileIo::read_with_flags`] for details.
+    fn write_with_flags(&self, reader: &mut VmReader, status_flags: StatusFlags) -> Result<usize> {
+        self.write(reader)
+    }
+
     fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
         return_errno_with_message!(Errno::EINVAL, "ioctl is not supported");
     }
+
+    /// Handles an ioctl that is not an [`IoctlCmd`].
+    ///
+    /// See [`FileLike::ioctl_raw`] for details.
+    fn ioctl_raw(&self, cmd: u32, arg: usize) -> Result<i32> {
+        return_errno_with_message!(Errno::EINVAL, "the ioctl command is unknown");
+    }
+
+    /// Seeks the file, if the file keeps the offset by itself.
+    ///
+    /// Returns `None` by default, in which case only the offset of the file handle is changed.
+    fn seek(&self, pos: SeekFrom) -> Option<Result<usize>> {
+        None
+    }
 }
@@ -1,5 +1,6 @@
 // SPDX-License-Identifier: MPL-2.0
 
+pub mod cgroupfs;
 pub mod device;
 pub mod devpts;
 pub mod epoll;
@@ -7,18 +8,29 @@ pub mod exfat;
 pub mod ext2;
 pub mod file_handle;
 pub mod file_table;
+pub mod flashfs;
 pub mod fs_resolver;
+pub mod hugetlbfs;
 pub mod inode_handle;
+pub mod mqueue;
 pub mod named_pipe;
+pub mod nfs;
+pub mod overlayfs;
 pub mod path;
 pub mod pipe;
 pub mod procfs;
+#[cfg(target_arch = "riscv64")]
+pub mod pstore;
 pub mod ramfs;
 pub mod rootfs;
 pub mod thread_info;
+pub mod tracefs;
 pub mod utils;
+pub mod vfat;
 
-use aster_block::BlockDevice;
+use aster_block::{partition::Partition, BlockDevice};
+use aster_mmc::MmcBlockDevice;
+use aster_usb::UsbStorageBlockDevice;
 use aster_virtio::device::block::device::BlockDevice as VirtIoBlockDevice;
 
 use crate::{
@@ -30,21 +42,43 @@ use crate::{
     prelude::*,
 };
 
+/// Starts the request handling thread of the block device, and registers its partitions.
+///
+/// Partitions do not need their own threads, since their requests are handled by the
+/// threads of the disks.
 fn start_block_device(device_name: &str) -> Result<Arc<dyn BlockDevice>> {
-    if let Some(device) = aster_block::get_device(device_name) {
-        let cloned_device = device.clone();
-        let task_fn = move || {
+    let Some(device) = aster_block::get_device(device_name) else {
+        return_errno_with_message!(Errno::ENOENT, "Device does not exist");
+    };
+    if device.downcast_ref::<Partition>().is_some() {
+        return Ok(device);
+    }
+
+    let cloned_device = device.clone();
+    let task_fn = move || {
+        if let Some(virtio_block_device) = cloned_device.downcast_ref::<VirtIoBlockDevice>() {
             info!("spawn the virt-io-block thread");
-            let virtio_block_device = cloned_device.downcast_ref::<VirtIoBlockDevice>().unwrap();
             loop {
                 virtio_block_device.handle_requests();
             }
-        };
-        crate::ThreadOptions::new(task_fn).spawn();
-        Ok(device)
-    } else {
-        return_errno_with_message!(Errno::ENOENT, "Device does not exist")
-    }
+        }
+        if let Some(usb_storage_device) = cloned_device.downcast_ref::<UsbStorageBlockDevice>() {
+            info!("spawn the usb-storage thread");
+            loop {
+                usb_storage_device.handle_requests();
+            }
+        }
+
+        let mmc_block_device = cloned_device.downcast_ref::<MmcBlockDevice>().unwrap();
+        info!("spawn the mmc-block thread");
+        loop {
+            mmc_block_device.handle_requests();
+        }
+    };
+    crate::ThreadOptions::new(task_fn).spawn();
+
+    aster_block::partition::scan_partitions(device_name);
+    Ok(device)
 }
 
 pub fn lazy_init() {
@@ -65,4 +99,18 @@ pub fn lazy_init() {
         println!("[kernel] Mount ExFat fs at {:?} ", target_path);
         self::rootfs::mount_fs_at(exfat_fs, &target_path).unwrap();
     }
+
+    // SD/MMC cards are named `mmcblkN` by `aster-mmc`, and USB disks are named
+    // `sdX` by `aster-usb`. They are not mounted automatically, but their request
+    // handling threads must be started, so that the disks and their partitions
+    // can be mounted later.
+    for (name, device) in aster_block::all_devices() {
+        let is_mmc =
+            name.starts_with("mmcblk") && device.downcast_ref::<MmcBlockDevice>().is_some();
+        let is_usb_storage =
+            name.starts_with("sd") && device.downcast_ref::<UsbStorageBlockDevice>().is_some();
+        if is_mmc || is_usb_storage {
+            let _ = start_block_device(&name);
+        }
+    }
 }
@@ -0,0 +1,475 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#![expect(unused_variables)]
+
+//! The mqueue filesystem.
+//!
+//! The filesystem contains the POSIX message queues, which are created and opened by
+//! `mq_open` via the internal mount of the filesystem. The filesystem can also be mounted
+//! (usually at "/dev/mqueue"), where the queues appear as files whose contents are the status
+//! of the queues.
+
+use core::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use spin::Once;
+
+use crate::{
+    events::IoEvents,
+    fs::{
+        file_handle::FileLike,
+        inode_handle::InodeHandle,
+        path::{Dentry, MountNode},
+        utils::{
+            DirentVisitor, FileSystem, FsFlags, Inode, InodeMode, InodeType, Metadata, SuperBlock,
+            NAME_MAX,
+        },
+    },
+    ipc::mqueue::MessageQueue,
+    prelude::*,
+    process::{signal::PollHandle, Gid, Process, Uid},
+    time::clocks::RealTimeCoarseClock,
+};
+
+const MQUEUE_MAGIC: u64 = 0x19800202;
+const BLOCK_SIZE: usize = 4096;
+const ROOT_INO: u64 = 1;
+
+/// The mqueue filesystem.
+///
+/// Like Linux, there is only one instance of the filesystem, so all the mounts of the
+/// filesystem share the same message queues.
+//
+// TODO: Support IPC namespaces, each of which has its own instance of the filesystem.
+pub struct MqueueFs {
+    sb: SuperBlock,
+    root: Arc<RootInode>,
+    next_ino: AtomicU64,
+}
+
+impl MqueueFs {
+    /// Returns the instance of the filesystem.
+    pub fn singleton() -> &'static Arc<Self> {
+        MQUEUE_FS.call_once(|| {
+            Arc::new_cyclic(|weak_self| Self {
+                sb: SuperBlock::new(MQUEUE_MAGIC, BLOCK_SIZE, NAME_MAX),
+                root: RootInode::new(weak_self.clone()),
+                next_ino: AtomicU64::new(ROOT_INO + 1),
+            })
+        })
+    }
+
+    /// Returns the root dentry of the internal mount.
+    pub fn root_dentry() -> Dentry {
+        let mount = MQUEUE_MOUNT.call_once(|| MountNode::new_root(Self::singleton().clone()));
+        Dentry::new_fs_root(mount.clone())
+    }
+
+    /// Creates a message queue named `name`.
+    ///
+    /// This method fails with [`EEXIST`] if the name has been used.
+    ///
+    /// [`EEXIST`]: crate::error::Errno::EEXIST
+    pub fn create_queue(
+        &self,
+        name: &str,
+        queue: MessageQueue,
+        mode: InodeMode,
+        uid: Uid,
+        gid: Gid,
+    ) -> Result<Arc<MqueueInode>> {
+        let ino = self.next_ino.fetch_add(1, Ordering::Relaxed);
+        let inode = MqueueInode::new(ino, queue, mode, uid, gid, self.root.fs.clone());
+        self.root.add_queue(name, inode.clone())?;
+        Ok(inode)
+    }
+
+    /// Returns the number of the message queues.
+    pub fn nr_queues(&self) -> usize {
+        self.root.queues.read().len()
+    }
+}
+
+impl FileSystem for MqueueFs {
+    fn sync(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn root_inode(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+
+    fn sb(&self) -> SuperBlock {
+        self.sb.clone()
+    }
+
+    fn flags(&self) -> FsFlags {
+        FsFlags::empty()
+    }
+}
+
+static MQUEUE_FS: Once<Arc<MqueueFs>> = Once::new();
+static MQUEUE_MOUNT: Once<Arc<MountNode>> = Once::new();
+
+struct RootInode {
+    queues: RwLock<BTreeMap<String, Arc<MqueueInode>>>,
+    metadata: RwLock<Metadata>,
+    fs: Weak<MqueueFs>,
+}
+
+impl RootInode {
+    fn new(fs: Weak<MqueueFs>) -> Arc<Self> {
+        Arc::new(Self {
+            queues: RwLock::new(BTreeMap::new()),
+            // Like Linux, everyone can create message queues in the directory, but only the
+            // owners can remove them.
+            metadata: RwLock::new(Metadata::new_dir(
+                ROOT_INO,
+                InodeMode::from_bits_truncate(0o1777),
+                BLOCK_SIZE,
+            )),
+            fs,
+        })
+    }
+
+    fn add_queue(&self, name: &str, inode: Arc<MqueueInode>) -> Result<()> {
+        if name.len() > NAME_MAX {
+            return_errno_with_message!(Errno::ENAMETOOLONG, "the name is too long");
+        }
+
+        let mut queues = self.queues.write();
+        if queues.contains_key(name) {
+            return_errno_with_message!(Errno::EEXIST, "the message queue exists");
+        }
+        queues.insert(name.to_string(), inode);
+        drop(queues);
+
+        let now = RealTimeCoarseClock::get().read_time();
+        let mut metadata = self.metadata.write();
+        metadata.mtime = now;
+        metadata.ctime = now;
+        Ok(())
+    }
+}
+
+impl Inode for RootInode {
+    fn size(&self) -> usize {
+        self.metadata.read().size
+    }
+
+    fn resize(&self, new_size: usize) -> Result<()> {
+        Err(Error::new(Errno::EISDIR))
+    }
+
+    fn metadata(&self) -> Metadata {
+        *self.metadata.read()
+    }
+
+    fn ino(&self) -> u64 {
+        ROOT_INO
+    }
+
+    fn type_(&self) -> InodeType {
+        InodeType::Dir
+    }
+
+    fn mode(&self) -> Result<InodeMode> {
+        Ok(self.metadata.read().mode)
+    }
+
+    fn set_mode(&self, mode: InodeMode) -> Result<()> {
+        self.metadata.write().mode = mode;
+        Ok(())
+    }
+
+    fn owner(&self) -> Result<Uid> {
+        Ok(self.metadata.read().uid)
+    }
+
+    fn set_owner(&self, uid: Uid) -> Result<()> {
+        self.metadata.write().uid = uid;
+        Ok(())
+    }
+
+    fn group(&self) -> Result<Gid> {
+        Ok(self.metadata.read().gid)
+    }
+
+    fn set_group(&self, gid: Gid) -> Result<()> {
+        self.metadata.write().gid = gid;
+        Ok(())
+    }
+
+    fn atime(&self) -> Duration {
+        self.metadata.read().atime
+    }
+
+    fn set_atime(&self, time: Duration) {
+        self.metadata.write().atime = time;
+    }
+
+    fn mtime(&self) -> Duration {
+        self.metadata.read().mtime
+    }
+
+    fn set_mtime(&self, time: Duration) {
+        self.metadata.write().mtime = time;
+    }
+
+    fn ctime(&self) -> Duration {
+        self.metadata.read().ctime
+    }
+
+    fn set_ctime(&self, time: Duration) {
+        self.metadata.write().ctime = time;
+    }
+
+    /// Creates a message queue with the default attributes, as `open` with `O_CREAT` does.
+    fn create(&self, name: &str, type_: InodeType, mode: InodeMode) -> Result<Arc<dyn Inode>> {
+        if type_ != InodeType::File {
+            return_errno_with_message!(Errno::EPERM, "only message queues can be created");
+        }
+
+        let credentials = current_thread!().as_posix_thread().unwrap().credentials();
+        let (uid, gid) = (credentials.fsuid(), credentials.fsgid());
+        let inode = self.fs.upgrade().unwrap().create_queue(
+            name,
+            MessageQueue::new_default(),
+            mode,
+            uid,
+            gid,
+        )?;
+        Ok(inode)
+    }
+
+    fn readdir_at(&self, offset: usize, visitor: &mut dyn DirentVisitor) -> Result<usize> {
+        let try_readdir = |offset: &mut usize, visitor: &mut dyn DirentVisitor| -> Result<()> {
+            // Read the 2 special entries.
+            if *offset == 0 {
+                visitor.visit(".", ROOT_INO, InodeType::Dir, *offset)?;
+                *offset += 1;
+            }
+            if *offset == 1 {
+                visitor.visit("..", ROOT_INO, InodeType::Dir, *offset)?;
+                *offset += 1;
+            }
+
+            // Read the message queues.
+            let queues = self.queues.read();
+            for (idx, (name, inode)) in queues.iter().enumerate().skip(*offset - 2) {
+                visitor.visit(name, inode.ino(), InodeType::File, idx + 2)?;
+                *offset = idx + 3;
+            }
+            Ok(())
+        };
+
+        let mut iterate_offset = offset;
+        match try_readdir(&mut iterate_offset, visitor) {
+            Err(e) if offset == iterate_offset => Err(e),
+            _ => Ok(iterate_offset - offset),
+        }
+    }
+
+    fn link(&self, old: &Arc<dyn Inode>, name: &str) -> Result<()> {
+        Err(Error::new(Errno::EPERM))
+    }
+
+    fn unlink(&self, name: &str) -> Result<()> {
+        if self.queues.write().remove(name).is_none() {
+            return_errno_with_message!(Errno::ENOENT, "the message queue does not exist");
+        }
+
+        let now = RealTimeCoarseClock::get().read_time();
+        let mut metadata = self.metadata.write();
+        metadata.mtime = now;
+        metadata.ctime = now;
+        Ok(())
+    }
+
+    fn rmdir(&self, name: &str) -> Result<()> {
+        Err(Error::new(Errno::ENOTDIR))
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>> {
+        let inode: Arc<dyn Inode> = match name {
+            "." | ".." => self.fs().root_inode(),
+            name => self.queues.read().get(name).cloned().ok_or_else(|| {
+                Error::with_message(Errno::ENOENT, "the message queue does not exist")
+            })?,
+        };
+        Ok(inode)
+    }
+
+    fn rename(&self, old_name: &str, target: &Arc<dyn Inode>, new_name: &str) -> Result<()> {
+        Err(Error::new(Errno::EPERM))
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        self.fs.upgrade().unwrap()
+    }
+
+    /// The message queues can be removed by `mq_unlink` without the dentries, so the dentries
+    /// cannot be cached.
+    fn is_dentry_cacheable(&self) -> bool {
+        false
+    }
+}
+
+/// An inode of a POSIX message queue.
+pub struct MqueueInode {
+    queue: MessageQueue,
+    metadata: RwLock<Metadata>,
+    fs: Weak<MqueueFs>,
+}
+
+impl MqueueInode {
+    fn new(
+        ino: u64,
+        queue: MessageQueue,
+        mode: InodeMode,
+        uid: Uid,
+        gid: Gid,
+        fs: Weak<MqueueFs>,
+    ) -> Arc<Self> {
+        let mut metadata = Metadata::new_file(ino, mode, BLOCK_SIZE);
+        metadata.uid = uid;
+        metadata.gid = gid;
+
+        Arc::new(Self {
+            queue,
+            metadata: RwLock::new(metadata),
+            fs,
+        })
+    }
+
+    /// Returns the message queue inode opened by the file.
+    ///
+    /// This method fails with [`EBADF`] if the file is not a message queue descriptor.
+    ///
+    /// [`EBADF`]: crate::error::Errno::EBADF
+    pub fn from_file(file: &dyn FileLike) -> Result<&Self> {
+        file.downcast_ref::<InodeHandle>()
+            .and_then(|inode_handle| inode_handle.dentry().inode().downcast_ref::<Self>())
+            .ok_or_else(|| Error::with_message(Errno::EBADF, "the file is not a message queue"))
+    }
+
+    pub fn queue(&self) -> &MessageQueue {
+        &self.queue
+    }
+}
+
+impl Inode for MqueueInode {
+    fn size(&self) -> usize {
+        0
+    }
+
+    fn resize(&self, new_size: usize) -> Result<()> {
+        Err(Error::new(Errno::EINVAL))
+    }
+
+    fn metadata(&self) -> Metadata {
+        *self.metadata.read()
+    }
+
+    fn ino(&self) -> u64 {
+        self.metadata.read().ino
+    }
+
+    fn type_(&self) -> InodeType {
+        InodeType::File
+    }
+
+    fn mode(&self) -> Result<InodeMode> {
+        Ok(self.metadata.read().mode)
+    }
+
+    fn set_mode(&self, mode: InodeMode) -> Result<()> {
+        self.metadata.write().mode = mode;
+        Ok(())
+    }
+
+    fn owner(&self) -> Result<Uid> {
+        Ok(self.metadata.read().uid)
+    }
+
+    fn set_owner(&self, uid: Uid) -> Result<()> {
+        self.metadata.write().uid = uid;
+        Ok(())
+    }
+
+    fn group(&self) -> Result<Gid> {
+        Ok(self.metadata.read().gid)
+    }
+
+    fn set_group(&self, gid: Gid) -> Result<()> {
+        self.metadata.write().gid = gid;
+        Ok(())
+    }
+
+    fn atime(&self) -> Duration {
+        self.metadata.read().atime
+    }
+
+    fn set_atime(&self, time: Duration) {
+        self.metadata.write().atime = time;
+    }
+
+    fn mtime(&self) -> Duration {
+        self.metadata.read().mtime
+    }
+
+    fn set_mtime(&self, time: Duration) {
+        self.metadata.write().mtime = time;
+    }
+
+    fn ctime(&self) -> Duration {
+        self.metadata.read().ctime
+    }
+
+    fn set_ctime(&self, time: Duration) {
+        self.metadata.write().ctime = time;
+    }
+
+    fn read_at(&self, offset: usize, writer: &mut VmWriter) -> Result<usize> {
+        let status = self.queue.status();
+        let Some(bytes) = status.as_bytes().get(offset..) else {
+            return Ok(0);
+        };
+        let len = writer.write_fallible(&mut bytes.into())?;
+        Ok(len)
+    }
+
+    fn read_direct_at(&self, offset: usize, writer: &mut VmWriter) -> Result<usize> {
+        self.read_at(offset, writer)
+    }
+
+    fn write_at(&self, offset: usize, reader: &mut VmReader) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "message queues cannot be written");
+    }
+
+    fn write_direct_at(&self, offset: usize, reader: &mut VmReader) -> Result<usize> {
+        self.write_at(offset, reader)
+    }
+
+    fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
+        self.queue.poll(mask, poller)
+    }
+
+    fn on_fd_close(&self) {
+        // Like Linux, the notification is removed when the registered process closes any of
+        // the file descriptors of the queue.
+        if let Some(process) = Process::current() {
+            self.queue.unregister_notify(process.pid());
+        }
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        self.fs.upgrade().unwrap()
+    }
+
+    fn is_dentry_cacheable(&self) -> bool {
+        false
+    }
+}
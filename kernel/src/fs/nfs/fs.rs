@@ -0,0 +1,332 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::time::Duration;
+
+use aster_bigtcp::wire::Ipv4Address;
+
+use super::{
+    inode::NfsInode,
+    proto::{
+        check_mount_status, check_status, Fattr, FileHandle, FsStat, MOUNTPROC3_MNT, MOUNT_PROGRAM,
+        MOUNT_VERSION, NFSPROC3_FSSTAT, NFSPROC3_GETATTR, NFS_PORT, NFS_PROGRAM, NFS_VERSION,
+    },
+    rpc::{getport, Protocol, RpcClient, Timeout},
+    xdr::{XdrReader, XdrWriter},
+};
+use crate::{
+    fs::utils::{FileSystem, FsFlags, Inode, SuperBlock, NAME_MAX},
+    prelude::*,
+};
+
+const NFS_SUPER_MAGIC: u64 = 0x6969;
+/// The unit of the I/O, which is the page size of the page cache.
+pub(super) const BLOCK_SIZE: usize = PAGE_SIZE;
+/// The largest length of the exported paths (`MNTPATHLEN`).
+const MAX_EXPORT_PATH_LEN: usize = 1024;
+
+/// An NFSv3 file system that is exported by a remote server.
+pub struct NfsFs {
+    client: RpcClient,
+    root: Arc<NfsInode>,
+    /// The inodes that are alive, indexed by their file IDs.
+    ///
+    /// An opened file is represented by one inode, even if it is looked up with different
+    /// paths, so that the names share the same page cache.
+    inodes: Mutex<BTreeMap<u64, Weak<NfsInode>>>,
+    server: Ipv4Address,
+    path: String,
+}
+
+impl NfsFs {
+    /// Mounts the directory that is exported by the server.
+    ///
+    /// The `source` is in the form of `<server-ip>:<path>`, and the `options` are the
+    /// comma-separated NFS mount options, e.g., `proto=udp,port=2049,timeo=11`.
+    pub fn mount(source: &str, options: &str) -> Result<Arc<Self>> {
+        let options = NfsMountOptions::parse(source, options)?;
+        Self::open(options)
+    }
+
+    fn open(options: NfsMountOptions) -> Result<Arc<Self>> {
+        let server = options.server;
+        let timeout = options.timeout;
+
+        let root_fh = {
+            let mount_port = match options.mount_port {
+                Some(port) => port,
+                None => getport(
+                    server,
+                    MOUNT_PROGRAM,
+                    MOUNT_VERSION,
+                    options.mount_protocol,
+                    timeout,
+                )?,
+            };
+            let client = RpcClient::connect(
+                server,
+                mount_port,
+                options.mount_protocol,
+                MOUNT_PROGRAM,
+                MOUNT_VERSION,
+                timeout,
+            )?;
+
+            let mut args = XdrWriter::new();
+            args.string(&options.path);
+            let reply = client.call(MOUNTPROC3_MNT, &args.into_bytes())?;
+            let mut reader = XdrReader::new(&reply);
+            check_mount_status(&mut reader)?;
+            // The authentication flavors that are accepted by the server follow, but only
+            // `AUTH_UNIX` is supported anyway.
+            FileHandle::decode(&mut reader)?
+        };
+
+        let port = match options.port {
+            Some(port) => port,
+            None => match getport(server, NFS_PROGRAM, NFS_VERSION, options.protocol, timeout) {
+                Ok(port) => port,
+                // Some servers do not register NFS to the port mapper, but they listen on the
+                // well-known port.
+                Err(err) if err.error() == Errno::EPROTONOSUPPORT => NFS_PORT,
+                Err(err) => return Err(err),
+            },
+        };
+        let client = RpcClient::connect(
+            server,
+            port,
+            options.protocol,
+            NFS_PROGRAM,
+            NFS_VERSION,
+            timeout,
+        )?;
+        let root_attr = getattr(&client, &root_fh)?;
+
+        let fs = Arc::new_cyclic(|weak_fs| {
+            let root = NfsInode::new(root_fh, root_attr, weak_fs.clone());
+            let mut inodes = BTreeMap::new();
+            inodes.insert(root.ino(), Arc::downgrade(&root));
+            Self {
+                client,
+                root,
+                inodes: Mutex::new(inodes),
+                server,
+                path: options.path,
+            }
+        });
+        info!(
+            "[NFS] mounted {}:{} over {:?}",
+            fs.server, fs.path, options.protocol
+        );
+        Ok(fs)
+    }
+
+    /// Calls an NFS procedure.
+    pub(super) fn call(&self, procedure: u32, args: &[u8]) -> Result<Vec<u8>> {
+        self.client.call(procedure, args)
+    }
+
+    pub(super) fn getattr(&self, fh: &FileHandle) -> Result<Fattr> {
+        getattr(&self.client, fh)
+    }
+
+    /// Returns the inode of the file, which is created if the file is not opened yet.
+    pub(super) fn get_inode(self: &Arc<Self>, fh: FileHandle, attr: Fattr) -> Arc<NfsInode> {
+        let cached = self.inodes.lock().get(&attr.fileid).and_then(Weak::upgrade);
+        if let Some(inode) = cached {
+            // The file ID may be reused after the file is removed by others.
+            if *inode.fh() == fh {
+                inode.revalidate(attr);
+                return inode;
+            }
+        }
+
+        let inode = NfsInode::new(fh, attr, Arc::downgrade(self));
+        self.inodes
+            .lock()
+            .insert(inode.ino(), Arc::downgrade(&inode));
+        inode
+    }
+
+    /// Forgets the inode that is being dropped.
+    pub(super) fn remove_inode(&self, ino: u64) {
+        let mut inodes = self.inodes.lock();
+        if inodes
+            .get(&ino)
+            .is_some_and(|inode| inode.strong_count() == 0)
+        {
+            inodes.remove(&ino);
+        }
+    }
+}
+
+impl FileSystem for NfsFs {
+    fn sync(&self) -> Result<()> {
+        let inodes: Vec<_> = self
+            .inodes
+            .lock()
+            .values()
+            .filter_map(Weak::upgrade)
+            .collect();
+        for inode in inodes {
+            inode.flush()?;
+        }
+        Ok(())
+    }
+
+    fn root_inode(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+
+    fn sb(&self) -> SuperBlock {
+        let mut sb = SuperBlock::new(NFS_SUPER_MAGIC, BLOCK_SIZE, NAME_MAX);
+        sb.fsid = u32::from(self.server) as u64;
+
+        let mut args = XdrWriter::new();
+        self.root.fh().encode(&mut args);
+        let stat = self
+            .call(NFSPROC3_FSSTAT, &args.into_bytes())
+            .and_then(|reply| {
+                let mut reader = XdrReader::new(&reply);
+                check_status(&mut reader)?;
+                FsStat::decode(&mut reader)
+            });
+        match stat {
+            Ok(stat) => {
+                sb.blocks = (stat.total_bytes / BLOCK_SIZE as u64) as usize;
+                sb.bfree = (stat.free_bytes / BLOCK_SIZE as u64) as usize;
+                sb.bavail = (stat.avail_bytes / BLOCK_SIZE as u64) as usize;
+                sb.files = stat.total_files as usize;
+                sb.ffree = stat.free_files as usize;
+            }
+            Err(err) => debug!("[NFS] cannot get the file system statistics: {:?}", err),
+        }
+        sb
+    }
+
+    fn flags(&self) -> FsFlags {
+        FsFlags::empty()
+    }
+}
+
+fn getattr(client: &RpcClient, fh: &FileHandle) -> Result<Fattr> {
+    let mut args = XdrWriter::new();
+    fh.encode(&mut args);
+    let reply = client.call(NFSPROC3_GETATTR, &args.into_bytes())?;
+    let mut reader = XdrReader::new(&reply);
+    check_status(&mut reader)?;
+    Fattr::decode(&mut reader)
+}
+
+/// The mount options of NFS.
+#[derive(Debug)]
+struct NfsMountOptions {
+    server: Ipv4Address,
+    path: String,
+    /// The port of the NFS service, or `None` to ask the port mapper.
+    port: Option<u16>,
+    protocol: Protocol,
+    /// The port of the MOUNT service, or `None` to ask the port mapper.
+    mount_port: Option<u16>,
+    mount_protocol: Protocol,
+    timeout: Timeout,
+}
+
+impl NfsMountOptions {
+    /// Parses the source like `10.0.2.2:/srv/nfs`, and the options like
+    /// `vers=3,proto=udp,timeo=11,retrans=3`.
+    ///
+    /// The options that do not apply to this client (e.g., `nolock` and `hard`) are accepted
+    /// and ignored, so that the usual command lines work.
+    fn parse(source: &str, options: &str) -> Result<Self> {
+        let Some((server, path)) = source.split_once(':') else {
+            return_errno_with_message!(Errno::EINVAL, "the source is not <server>:<path>");
+        };
+        let mut server: Ipv4Address = server
+            .parse()
+            .map_err(|_| Error::with_message(Errno::EINVAL, "the server is not an IPv4 address"))?;
+        if !path.starts_with('/') || path.len() > MAX_EXPORT_PATH_LEN {
+            return_errno_with_message!(Errno::EINVAL, "invalid exported path");
+        }
+
+        let mut port = None;
+        let mut protocol = Protocol::Tcp;
+        let mut mount_port = None;
+        let mut mount_protocol = None;
+        let mut timeo = None;
+        let mut retrans = None;
+
+        for option in options.split(',').filter(|option| !option.is_empty()) {
+            let (key, value) = option.split_once('=').unwrap_or((option, ""));
+            match key {
+                "addr" => {
+                    server = value.parse().map_err(|_| {
+                        Error::with_message(Errno::EINVAL, "the server is not an IPv4 address")
+                    })?;
+                }
+                // A zero port means asking the port mapper.
+                "port" => port = Some(parse_number(value)?).filter(|port| *port != 0),
+                "mountport" => mount_port = Some(parse_number(value)?).filter(|port| *port != 0),
+                "proto" => protocol = parse_protocol(value)?,
+                "tcp" => protocol = Protocol::Tcp,
+                "udp" => protocol = Protocol::Udp,
+                "mountproto" => mount_protocol = Some(parse_protocol(value)?),
+                "vers" | "nfsvers" | "mountvers" => {
+                    if value != "3" {
+                        warn!("unsupported NFS version: {}", value);
+                        return_errno_with_message!(
+                            Errno::EPROTONOSUPPORT,
+                            "only NFSv3 is supported"
+                        );
+                    }
+                }
+                "v3" => {}
+                "timeo" => timeo = Some(parse_number::<u32>(value)?),
+                "retrans" => retrans = Some(parse_number(value)?),
+                "sec" if value == "sys" => {}
+                "hard" | "soft" | "intr" | "nointr" | "lock" | "nolock" | "local_lock" | "ac"
+                | "noac" | "actimeo" | "acregmin" | "acregmax" | "acdirmin" | "acdirmax"
+                | "rsize" | "wsize" | "namlen" | "clientaddr" | "mountaddr" | "resvport"
+                | "noresvport" | "cto" | "nocto" | "sharecache" | "nosharecache" => {}
+                _ => {
+                    warn!("unsupported NFS mount option: {}", option);
+                    return_errno_with_message!(Errno::EINVAL, "unsupported mount option");
+                }
+            }
+        }
+
+        // The defaults of Linux, where `timeo` is in deciseconds.
+        let (default_timeo, default_retrans) = match protocol {
+            Protocol::Tcp => (600, 2),
+            Protocol::Udp => (11, 3),
+        };
+        let timeo = timeo.filter(|timeo| *timeo != 0).unwrap_or(default_timeo);
+        let timeout = Timeout {
+            timeo: Duration::from_millis(timeo as u64 * 100),
+            retrans: retrans.unwrap_or(default_retrans),
+        };
+
+        Ok(Self {
+            server,
+            path: path.to_string(),
+            port,
+            protocol,
+            mount_port,
+            mount_protocol: mount_protocol.unwrap_or(protocol),
+            timeout,
+        })
+    }
+}
+
+fn parse_protocol(value: &str) -> Result<Protocol> {
+    match value {
+        "tcp" => Ok(Protocol::Tcp),
+        "udp" => Ok(Protocol::Udp),
+        _ => return_errno_with_message!(Errno::EPROTONOSUPPORT, "unsupported transport protocol"),
+    }
+}
+
+fn parse_number<T: core::str::FromStr>(value: &str) -> Result<T> {
+    value
+        .parse()
+        .map_err(|_| Error::with_message(Errno::EINVAL, "invalid number"))
+}
@@ -0,0 +1,928 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::{
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use aster_block::bio::BioWaiter;
+use aster_rights::Full;
+use ostd::mm::UntypedMem;
+
+use super::{
+    fs::{NfsFs, BLOCK_SIZE},
+    proto::{
+        check_status, decode_wcc_data, ftype, Fattr, FileHandle, Sattr, CREATE_GUARDED, FILE_SYNC,
+        MAX_PATH_LEN, NFSPROC3_CREATE, NFSPROC3_LINK, NFSPROC3_LOOKUP, NFSPROC3_MKDIR,
+        NFSPROC3_MKNOD, NFSPROC3_READ, NFSPROC3_READDIRPLUS, NFSPROC3_READLINK, NFSPROC3_REMOVE,
+        NFSPROC3_RENAME, NFSPROC3_RMDIR, NFSPROC3_SETATTR, NFSPROC3_SYMLINK, NFSPROC3_WRITE,
+    },
+    xdr::{XdrReader, XdrWriter},
+};
+use crate::{
+    fs::{
+        device::DeviceId,
+        utils::{
+            CachePage, DirentVisitor, FileSystem, Inode, InodeMode, InodeType, Metadata, MknodType,
+            PageCache, PageCacheBackend, NAME_MAX,
+        },
+    },
+    prelude::*,
+    process::{Gid, Uid},
+    time::clocks::MonotonicCoarseClock,
+    vm::vmo::Vmo,
+};
+
+/// How long the cached attributes are trusted, like the default `acregmin` of Linux.
+const ATTR_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// The largest size of the replies of `READDIRPLUS`.
+const READDIR_MAX_COUNT: u32 = 32 * 1024;
+
+/// An inode of the NFS file system.
+///
+/// The attributes are cached for [`ATTR_TIMEOUT`]. The data of a regular file is cached in the
+/// page cache, which is written back when the file is closed or synchronized, and is dropped
+/// when the file is found changed by others.
+pub(super) struct NfsInode {
+    fh: FileHandle,
+    ino: u64,
+    type_: InodeType,
+    attr: Mutex<CachedAttr>,
+    /// The size of the regular file, which is ahead of the server until the page cache is
+    /// written back.
+    ///
+    /// It is not protected by `attr`, since it is read by the page cache backend, which may
+    /// be called with the page cache locked.
+    size: AtomicUsize,
+    /// Whether the page cache has been written since the last write-back.
+    dirty: AtomicBool,
+    page_cache: Option<PageCache>,
+    /// The entries of the directory, which are listed when reading from the first entry.
+    entries: Mutex<Vec<DirEntry>>,
+    this: Weak<NfsInode>,
+    fs: Weak<NfsFs>,
+}
+
+struct CachedAttr {
+    attr: Fattr,
+    expires_at: Duration,
+}
+
+struct DirEntry {
+    name: String,
+    ino: u64,
+    type_: InodeType,
+}
+
+impl NfsInode {
+    pub(super) fn new(fh: FileHandle, attr: Fattr, fs: Weak<NfsFs>) -> Arc<Self> {
+        Arc::new_cyclic(|weak_self| {
+            let page_cache = (attr.type_ == InodeType::File).then(|| {
+                PageCache::with_capacity(attr.size as usize, weak_self.clone() as _).unwrap()
+            });
+            Self {
+                fh,
+                ino: attr.fileid,
+                type_: attr.type_,
+                size: AtomicUsize::new(attr.size as usize),
+                dirty: AtomicBool::new(false),
+                page_cache,
+                attr: Mutex::new(CachedAttr {
+                    attr,
+                    expires_at: now() + ATTR_TIMEOUT,
+                }),
+                entries: Mutex::new(Vec::new()),
+                this: weak_self.clone(),
+                fs,
+            }
+        })
+    }
+
+    pub(super) fn fh(&self) -> &FileHandle {
+        &self.fh
+    }
+
+    fn nfs_fs(&self) -> Arc<NfsFs> {
+        self.fs.upgrade().unwrap()
+    }
+
+    /// Returns the attributes, which are fetched again if the cached ones expire.
+    fn attr(&self) -> Fattr {
+        {
+            let cached = self.attr.lock();
+            if now() < cached.expires_at {
+                return cached.attr.clone();
+            }
+        }
+
+        match self.nfs_fs().getattr(&self.fh) {
+            Ok(attr) => {
+                self.revalidate(attr.clone());
+                attr
+            }
+            Err(err) => {
+                debug!(
+                    "[NFS] cannot get the attributes of {:?}: {:?}",
+                    self.fh, err
+                );
+                self.attr.lock().attr.clone()
+            }
+        }
+    }
+
+    /// Caches the attributes from the server, and drops the cached data if the file is
+    /// changed by others.
+    ///
+    /// The data is kept if the page cache has been written, since the written data is newer
+    /// than the server.
+    pub(super) fn revalidate(&self, attr: Fattr) {
+        if let Some(page_cache) = &self.page_cache {
+            let changed = {
+                let cached = self.attr.lock();
+                attr.mtime != cached.attr.mtime || attr.size != cached.attr.size
+            };
+            // The attributes must not be locked here, since dropping the pages writes back
+            // the ones that are dirtied by the mappings, which updates the attributes.
+            if changed && !self.dirty.load(Ordering::Acquire) {
+                debug!("[NFS] {:?} is changed, drop the cached data", self.fh);
+                let pages = page_cache.pages();
+                let invalidated = pages
+                    .decommit(0..pages.size())
+                    .and_then(|_| page_cache.resize(attr.size as usize));
+                if let Err(err) = invalidated {
+                    warn!("[NFS] cannot drop the cached data: {:?}", err);
+                }
+                self.size.store(attr.size as usize, Ordering::Release);
+            }
+        }
+        self.store_attr(attr);
+    }
+
+    /// Caches the attributes that result from the changes of this client.
+    fn store_attr(&self, attr: Fattr) {
+        *self.attr.lock() = CachedAttr {
+            attr,
+            expires_at: now() + ATTR_TIMEOUT,
+        };
+    }
+
+    /// Forces the attributes to be fetched again.
+    fn expire_attr(&self) {
+        self.attr.lock().expires_at = Duration::ZERO;
+    }
+
+    /// Writes the page cache back to the server.
+    pub(super) fn flush(&self) -> Result<()> {
+        let Some(page_cache) = &self.page_cache else {
+            return Ok(());
+        };
+        // The file may be written again before the write-back completes.
+        self.dirty.store(false, Ordering::Release);
+        if let Err(err) = page_cache.evict_range(0..self.size()) {
+            self.dirty.store(true, Ordering::Release);
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    fn check_dir(&self) -> Result<()> {
+        if self.type_ != InodeType::Dir {
+            return_errno_with_message!(Errno::ENOTDIR, "the inode is not a directory");
+        }
+        Ok(())
+    }
+
+    /// Encodes the directory and a name in it (`diropargs3`).
+    fn encode_dirop(&self, args: &mut XdrWriter, name: &str) -> Result<()> {
+        if name.len() > NAME_MAX {
+            return_errno!(Errno::ENAMETOOLONG);
+        }
+        self.fh.encode(args);
+        args.string(name);
+        Ok(())
+    }
+
+    /// Updates the attributes of the directory after it is changed.
+    fn update_dir(&self, attr: Option<Fattr>) {
+        match attr {
+            Some(attr) => self.store_attr(attr),
+            None => self.expire_attr(),
+        }
+    }
+
+    fn lookup_fh(&self, name: &str) -> Result<(FileHandle, Fattr)> {
+        let mut args = XdrWriter::new();
+        self.encode_dirop(&mut args, name)?;
+        let fs = self.nfs_fs();
+        let reply = fs.call(NFSPROC3_LOOKUP, &args.into_bytes())?;
+
+        let mut reader = XdrReader::new(&reply);
+        check_status(&mut reader)?;
+        let fh = FileHandle::decode(&mut reader)?;
+        let attr = match Fattr::decode_optional(&mut reader)? {
+            Some(attr) => attr,
+            None => fs.getattr(&fh)?,
+        };
+        Ok((fh, attr))
+    }
+
+    /// Calls a procedure that creates a file in the directory, and returns the new inode.
+    fn create_child(&self, procedure: u32, name: &str, args: &[u8]) -> Result<Arc<NfsInode>> {
+        let fs = self.nfs_fs();
+        let reply = fs.call(procedure, args)?;
+
+        let mut reader = XdrReader::new(&reply);
+        check_status(&mut reader)?;
+        let fh = FileHandle::decode_optional(&mut reader)?;
+        let attr = Fattr::decode_optional(&mut reader)?;
+        self.update_dir(decode_wcc_data(&mut reader)?);
+
+        // The server may omit the handle and the attributes of the new file.
+        let (fh, attr) = match (fh, attr) {
+            (Some(fh), Some(attr)) => (fh, attr),
+            _ => self.lookup_fh(name)?,
+        };
+        Ok(fs.get_inode(fh, attr))
+    }
+
+    /// Calls a procedure that removes a file from the directory.
+    fn remove_child(&self, procedure: u32, name: &str) -> Result<()> {
+        let mut args = XdrWriter::new();
+        self.encode_dirop(&mut args, name)?;
+        let reply = self.nfs_fs().call(procedure, &args.into_bytes())?;
+
+        let mut reader = XdrReader::new(&reply);
+        check_status(&mut reader)?;
+        self.update_dir(decode_wcc_data(&mut reader)?);
+        Ok(())
+    }
+
+    fn create_symlink(&self, name: &str, mode: InodeMode, target: &str) -> Result<Arc<NfsInode>> {
+        if target.len() > MAX_PATH_LEN {
+            return_errno!(Errno::ENAMETOOLONG);
+        }
+        let mut args = XdrWriter::new();
+        self.encode_dirop(&mut args, name)?;
+        Sattr::with_mode(mode).encode(&mut args);
+        args.string(target);
+        self.create_child(NFSPROC3_SYMLINK, name, &args.into_bytes())
+    }
+
+    fn mknod_child(
+        &self,
+        name: &str,
+        mode: InodeMode,
+        type_: InodeType,
+        device: Option<DeviceId>,
+    ) -> Result<Arc<NfsInode>> {
+        let mut args = XdrWriter::new();
+        self.encode_dirop(&mut args, name)?;
+        args.u32(ftype(type_));
+        Sattr::with_mode(mode).encode(&mut args);
+        if let Some(device) = device {
+            args.u32(device.major()).u32(device.minor());
+        }
+        self.create_child(NFSPROC3_MKNOD, name, &args.into_bytes())
+    }
+
+    fn setattr(&self, sattr: Sattr) -> Result<()> {
+        let mut args = XdrWriter::new();
+        self.fh.encode(&mut args);
+        sattr.encode(&mut args);
+        // No guard on the change time.
+        args.bool(false);
+        let reply = self.nfs_fs().call(NFSPROC3_SETATTR, &args.into_bytes())?;
+
+        let mut reader = XdrReader::new(&reply);
+        check_status(&mut reader)?;
+        match decode_wcc_data(&mut reader)? {
+            Some(attr) => self.store_attr(attr),
+            None => self.expire_attr(),
+        }
+        Ok(())
+    }
+
+    /// Sets the time of the file, where the failure is only logged since there is no way to
+    /// report it.
+    fn set_time(&self, sattr: Sattr) {
+        if let Err(err) = self.setattr(sattr) {
+            debug!("[NFS] cannot set the time of {:?}: {:?}", self.fh, err);
+        }
+    }
+
+    /// Reads the file from the server until `buf` is full or the end of the file is reached.
+    fn read_remote(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        let fs = self.nfs_fs();
+        let mut read_len = 0;
+        while read_len < buf.len() {
+            let mut args = XdrWriter::new();
+            self.fh.encode(&mut args);
+            args.u64((offset + read_len) as u64)
+                .u32((buf.len() - read_len) as u32);
+            let reply = fs.call(NFSPROC3_READ, &args.into_bytes())?;
+
+            let mut reader = XdrReader::new(&reply);
+            check_status(&mut reader)?;
+            if let Some(attr) = Fattr::decode_optional(&mut reader)? {
+                self.store_attr(attr);
+            }
+            let _count = reader.u32()?;
+            let eof = reader.bool()?;
+            let data = reader.opaque(buf.len() - read_len)?;
+            buf[read_len..read_len + data.len()].copy_from_slice(data);
+            read_len += data.len();
+
+            if eof || data.is_empty() {
+                break;
+            }
+        }
+        Ok(read_len)
+    }
+
+    /// Writes the data to the stable storage of the server.
+    fn write_remote(&self, offset: usize, data: &[u8]) -> Result<()> {
+        let fs = self.nfs_fs();
+        let mut written_len = 0;
+        while written_len < data.len() {
+            let chunk = &data[written_len..];
+            let mut args = XdrWriter::new();
+            self.fh.encode(&mut args);
+            args.u64((offset + written_len) as u64)
+                .u32(chunk.len() as u32)
+                .u32(FILE_SYNC)
+                .opaque(chunk);
+            let reply = fs.call(NFSPROC3_WRITE, &args.into_bytes())?;
+
+            let mut reader = XdrReader::new(&reply);
+            check_status(&mut reader)?;
+            if let Some(attr) = decode_wcc_data(&mut reader)? {
+                self.store_attr(attr);
+            }
+            let count = reader.u32()? as usize;
+            if count == 0 || count > chunk.len() {
+                return_errno_with_message!(Errno::EIO, "the server writes nothing");
+            }
+            written_len += count;
+        }
+        Ok(())
+    }
+
+    /// Lists the entries of the directory with `READDIRPLUS`, which returns the file types.
+    fn list_entries(&self) -> Result<Vec<DirEntry>> {
+        let fs = self.nfs_fs();
+        let mut entries = Vec::new();
+        let mut cookie = 0u64;
+        let mut cookie_verf = [0u8; 8];
+
+        loop {
+            let mut args = XdrWriter::new();
+            self.fh.encode(&mut args);
+            args.u64(cookie)
+                .fixed_opaque(&cookie_verf)
+                .u32(READDIR_MAX_COUNT / 4)
+                .u32(READDIR_MAX_COUNT);
+            let reply = fs.call(NFSPROC3_READDIRPLUS, &args.into_bytes())?;
+
+            let mut reader = XdrReader::new(&reply);
+            check_status(&mut reader)?;
+            if let Some(attr) = Fattr::decode_optional(&mut reader)? {
+                self.store_attr(attr);
+            }
+            cookie_verf.copy_from_slice(reader.fixed_opaque(8)?);
+
+            let mut nr_entries = 0;
+            while reader.bool()? {
+                let ino = reader.u64()?;
+                let name = reader.string(NAME_MAX)?;
+                cookie = reader.u64()?;
+                let attr = Fattr::decode_optional(&mut reader)?;
+                let _fh = FileHandle::decode_optional(&mut reader)?;
+                // The type is unknown if the server omits the attributes.
+                let type_ = attr.map_or(InodeType::File, |attr| attr.type_);
+                entries.push(DirEntry { name, ino, type_ });
+                nr_entries += 1;
+            }
+            let eof = reader.bool()?;
+
+            if eof || nr_entries == 0 {
+                return Ok(entries);
+            }
+        }
+    }
+}
+
+impl PageCacheBackend for NfsInode {
+    fn read_page_async(&self, idx: usize, frame: &CachePage) -> Result<BioWaiter> {
+        let mut buf = vec![0u8; PAGE_SIZE];
+        self.read_remote(idx * PAGE_SIZE, &mut buf)?;
+        frame.writer().write(&mut VmReader::from(buf.as_slice()));
+        Ok(BioWaiter::new())
+    }
+
+    fn write_page_async(&self, idx: usize, frame: &CachePage) -> Result<BioWaiter> {
+        // Do not extend the file to the page boundary.
+        let offset = idx * PAGE_SIZE;
+        let len = self
+            .size
+            .load(Ordering::Acquire)
+            .saturating_sub(offset)
+            .min(PAGE_SIZE);
+        if len > 0 {
+            let mut buf = vec![0u8; len];
+            frame.reader().read(&mut VmWriter::from(buf.as_mut_slice()));
+            self.write_remote(offset, &buf)?;
+        }
+        Ok(BioWaiter::new())
+    }
+
+    fn npages(&self) -> usize {
+        self.size.load(Ordering::Acquire).div_ceil(PAGE_SIZE)
+    }
+}
+
+impl Inode for NfsInode {
+    fn size(&self) -> usize {
+        match self.type_ {
+            InodeType::File => self.size.load(Ordering::Acquire),
+            _ => self.attr().size as usize,
+        }
+    }
+
+    fn resize(&self, new_size: usize) -> Result<()> {
+        match self.type_ {
+            InodeType::File => {}
+            InodeType::Dir => return_errno!(Errno::EISDIR),
+            _ => return_errno!(Errno::EINVAL),
+        }
+
+        let page_cache = self.page_cache.as_ref().unwrap();
+        page_cache.resize(new_size)?;
+        self.size.store(new_size, Ordering::Release);
+        self.setattr(Sattr {
+            size: Some(new_size as u64),
+            ..Default::default()
+        })
+    }
+
+    fn metadata(&self) -> Metadata {
+        let attr = self.attr();
+        let rdev = match attr.type_ {
+            InodeType::CharDevice | InodeType::BlockDevice => {
+                DeviceId::new(attr.rdev.0, attr.rdev.1).into()
+            }
+            _ => 0,
+        };
+        Metadata {
+            dev: 0,
+            ino: self.ino,
+            size: self.size(),
+            blk_size: BLOCK_SIZE,
+            blocks: (attr.used as usize).div_ceil(512),
+            atime: attr.atime,
+            mtime: attr.mtime,
+            ctime: attr.ctime,
+            btime: None,
+            type_: self.type_,
+            mode: attr.mode,
+            nlinks: attr.nlink as usize,
+            uid: Uid::new(attr.uid),
+            gid: Gid::new(attr.gid),
+            rdev,
+        }
+    }
+
+    fn ino(&self) -> u64 {
+        self.ino
+    }
+
+    fn type_(&self) -> InodeType {
+        self.type_
+    }
+
+    fn mode(&self) -> Result<InodeMode> {
+        Ok(self.attr().mode)
+    }
+
+    fn set_mode(&self, mode: InodeMode) -> Result<()> {
+        self.setattr(Sattr::with_mode(mode))
+    }
+
+    fn owner(&self) -> Result<Uid> {
+        Ok(Uid::new(self.attr().uid))
+    }
+
+    fn set_owner(&self, uid: Uid) -> Result<()> {
+        self.setattr(Sattr {
+            uid: Some(uid.into()),
+            ..Default::default()
+        })
+    }
+
+    fn group(&self) -> Result<Gid> {
+        Ok(Gid::new(self.attr().gid))
+    }
+
+    fn set_group(&self, gid: Gid) -> Result<()> {
+        self.setattr(Sattr {
+            gid: Some(gid.into()),
+            ..Default::default()
+        })
+    }
+
+    fn atime(&self) -> Duration {
+        self.attr().atime
+    }
+
+    fn set_atime(&self, time: Duration) {
+        self.set_time(Sattr {
+            atime: Some(time),
+            ..Default::default()
+        });
+    }
+
+    fn mtime(&self) -> Duration {
+        self.attr().mtime
+    }
+
+    fn set_mtime(&self, time: Duration) {
+        self.set_time(Sattr {
+            mtime: Some(time),
+            ..Default::default()
+        });
+    }
+
+    fn ctime(&self) -> Duration {
+        self.attr().ctime
+    }
+
+    fn set_ctime(&self, _time: Duration) {
+        // The change time is maintained by the server.
+    }
+
+    fn page_cache(&self) -> Option<Vmo<Full>> {
+        self.page_cache
+            .as_ref()
+            .map(|page_cache| page_cache.pages().dup())
+    }
+
+    fn read_at(&self, offset: usize, writer: &mut VmWriter) -> Result<usize> {
+        match self.type_ {
+            InodeType::File => {}
+            InodeType::Dir => return_errno!(Errno::EISDIR),
+            _ => return_errno!(Errno::EINVAL),
+        }
+
+        // Check whether the file is changed by others.
+        self.attr();
+
+        let page_cache = self.page_cache.as_ref().unwrap();
+        let (read_off, read_len) = {
+            let file_size = self.size();
+            let start = file_size.min(offset);
+            let end = file_size.min(offset + writer.avail());
+            (start, end - start)
+        };
+        page_cache.pages().read(read_off, writer)?;
+        Ok(read_len)
+    }
+
+    fn read_direct_at(&self, offset: usize, writer: &mut VmWriter) -> Result<usize> {
+        self.read_at(offset, writer)
+    }
+
+    fn write_at(&self, offset: usize, reader: &mut VmReader) -> Result<usize> {
+        match self.type_ {
+            InodeType::File => {}
+            InodeType::Dir => return_errno!(Errno::EISDIR),
+            _ => return_errno!(Errno::EINVAL),
+        }
+
+        let page_cache = self.page_cache.as_ref().unwrap();
+        let write_len = reader.remain();
+        let new_size = offset + write_len;
+        self.dirty.store(true, Ordering::Release);
+        if new_size > self.size() {
+            page_cache.resize(new_size)?;
+            self.size.store(new_size, Ordering::Release);
+        }
+        page_cache.pages().write(offset, reader)?;
+        Ok(write_len)
+    }
+
+    fn write_direct_at(&self, offset: usize, reader: &mut VmReader) -> Result<usize> {
+        let written_len = self.write_at(offset, reader)?;
+        self.flush()?;
+        Ok(written_len)
+    }
+
+    fn create(&self, name: &str, type_: InodeType, mode: InodeMode) -> Result<Arc<dyn Inode>> {
+        self.check_dir()?;
+
+        let mut args = XdrWriter::new();
+        self.encode_dirop(&mut args, name)?;
+        let inode = match type_ {
+            InodeType::File => {
+                args.u32(CREATE_GUARDED);
+                Sattr::with_mode(mode).encode(&mut args);
+                self.create_child(NFSPROC3_CREATE, name, &args.into_bytes())?
+            }
+            InodeType::Dir => {
+                Sattr::with_mode(mode).encode(&mut args);
+                self.create_child(NFSPROC3_MKDIR, name, &args.into_bytes())?
+            }
+            // The target is required to create a symbolic link, which is given later.
+            InodeType::SymLink => {
+                return Ok(Arc::new(PendingSymlink {
+                    dir: self.this.upgrade().unwrap(),
+                    name: name.to_string(),
+                    mode,
+                    created: Mutex::new(None),
+                }));
+            }
+            InodeType::NamedPipe | InodeType::Socket => {
+                self.mknod_child(name, mode, type_, None)?
+            }
+            InodeType::CharDevice | InodeType::BlockDevice => {
+                return_errno_with_message!(Errno::EINVAL, "use mknod to create the device files")
+            }
+        };
+        Ok(inode)
+    }
+
+    fn mknod(&self, name: &str, mode: InodeMode, type_: MknodType) -> Result<Arc<dyn Inode>> {
+        self.check_dir()?;
+
+        let inode = match type_ {
+            MknodType::NamedPipeNode => self.mknod_child(name, mode, InodeType::NamedPipe, None)?,
+            MknodType::CharDeviceNode(device) => {
+                self.mknod_child(name, mode, InodeType::CharDevice, Some(device.id()))?
+            }
+            MknodType::BlockDeviceNode(device) => {
+                self.mknod_child(name, mode, InodeType::BlockDevice, Some(device.id()))?
+            }
+        };
+        Ok(inode)
+    }
+
+    fn readdir_at(&self, offset: usize, visitor: &mut dyn DirentVisitor) -> Result<usize> {
+        self.check_dir()?;
+
+        // The listing is fetched again when reading from the start, and the later reads
+        // continue with the same listing.
+        let mut entries = self.entries.lock();
+        if offset == 0 || entries.is_empty() {
+            *entries = self.list_entries()?;
+        }
+
+        let try_readdir = |offset: &mut usize, visitor: &mut dyn DirentVisitor| -> Result<()> {
+            for (idx, entry) in entries.iter().enumerate().skip(*offset) {
+                visitor.visit(&entry.name, entry.ino, entry.type_, idx)?;
+                *offset = idx + 1;
+            }
+            Ok(())
+        };
+
+        let mut iterate_offset = offset;
+        match try_readdir(&mut iterate_offset, visitor) {
+            Err(e) if offset == iterate_offset => Err(e),
+            _ => Ok(iterate_offset - offset),
+        }
+    }
+
+    fn link(&self, old: &Arc<dyn Inode>, name: &str) -> Result<()> {
+        self.check_dir()?;
+        let old = old
+            .downcast_ref::<NfsInode>()
+            .filter(|old| Weak::ptr_eq(&old.fs, &self.fs))
+            .ok_or_else(|| Error::with_message(Errno::EXDEV, "not the same filesystem"))?;
+
+        let mut args = XdrWriter::new();
+        old.fh.encode(&mut args);
+        self.encode_dirop(&mut args, name)?;
+        let reply = self.nfs_fs().call(NFSPROC3_LINK, &args.into_bytes())?;
+
+        let mut reader = XdrReader::new(&reply);
+        check_status(&mut reader)?;
+        match Fattr::decode_optional(&mut reader)? {
+            Some(attr) => old.store_attr(attr),
+            None => old.expire_attr(),
+        }
+        self.update_dir(decode_wcc_data(&mut reader)?);
+        Ok(())
+    }
+
+    fn unlink(&self, name: &str) -> Result<()> {
+        self.check_dir()?;
+        if name == "." || name == ".." {
+            return_errno_with_message!(Errno::EISDIR, "unlink . or ..");
+        }
+        self.remove_child(NFSPROC3_REMOVE, name)
+    }
+
+    fn rmdir(&self, name: &str) -> Result<()> {
+        self.check_dir()?;
+        match name {
+            "." => return_errno_with_message!(Errno::EINVAL, "rmdir ."),
+            ".." => return_errno_with_message!(Errno::ENOTEMPTY, "rmdir .."),
+            _ => self.remove_child(NFSPROC3_RMDIR, name),
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>> {
+        self.check_dir()?;
+        let (fh, attr) = self.lookup_fh(name)?;
+        Ok(self.nfs_fs().get_inode(fh, attr))
+    }
+
+    fn rename(&self, old_name: &str, target: &Arc<dyn Inode>, new_name: &str) -> Result<()> {
+        if [old_name, new_name]
+            .iter()
+            .any(|name| *name == "." || *name == "..")
+        {
+            return_errno_with_message!(Errno::EISDIR, "rename . or ..");
+        }
+        let target = target
+            .downcast_ref::<NfsInode>()
+            .filter(|target| Weak::ptr_eq(&target.fs, &self.fs))
+            .ok_or_else(|| Error::with_message(Errno::EXDEV, "not the same filesystem"))?;
+        self.check_dir()?;
+        target.check_dir()?;
+
+        let mut args = XdrWriter::new();
+        self.encode_dirop(&mut args, old_name)?;
+        target.encode_dirop(&mut args, new_name)?;
+        let reply = self.nfs_fs().call(NFSPROC3_RENAME, &args.into_bytes())?;
+
+        let mut reader = XdrReader::new(&reply);
+        check_status(&mut reader)?;
+        self.update_dir(decode_wcc_data(&mut reader)?);
+        target.update_dir(decode_wcc_data(&mut reader)?);
+        Ok(())
+    }
+
+    fn read_link(&self) -> Result<String> {
+        if self.type_ != InodeType::SymLink {
+            return_errno_with_message!(Errno::EINVAL, "the inode is not a symbolic link");
+        }
+
+        let mut args = XdrWriter::new();
+        self.fh.encode(&mut args);
+        let reply = self.nfs_fs().call(NFSPROC3_READLINK, &args.into_bytes())?;
+
+        let mut reader = XdrReader::new(&reply);
+        check_status(&mut reader)?;
+        if let Some(attr) = Fattr::decode_optional(&mut reader)? {
+            self.store_attr(attr);
+        }
+        reader.string(MAX_PATH_LEN)
+    }
+
+    fn write_link(&self, _target: &str) -> Result<()> {
+        return_errno_with_message!(Errno::EEXIST, "the symbolic link has been created");
+    }
+
+    fn sync_all(&self) -> Result<()> {
+        self.flush()
+    }
+
+    fn sync_data(&self) -> Result<()> {
+        self.flush()
+    }
+
+    fn on_fd_close(&self) {
+        // Write the data back on close, so that other clients see it when they open the file.
+        if let Err(err) = self.flush() {
+            warn!("[NFS] cannot write back {:?}: {:?}", self.fh, err);
+        }
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        self.nfs_fs()
+    }
+}
+
+impl Drop for NfsInode {
+    fn drop(&mut self) {
+        // The file system is being dropped if it cannot be upgraded.
+        let Some(fs) = self.fs.upgrade() else {
+            return;
+        };
+        fs.remove_inode(self.ino);
+    }
+}
+
+/// A symbolic link that is created by [`Inode::create`], whose target is not given yet.
+///
+/// NFS creates a symbolic link with its target, so the link is created on the server in
+/// [`Inode::write_link`]. The inode is not cached by the dentries, so later lookups find the
+/// created link.
+struct PendingSymlink {
+    dir: Arc<NfsInode>,
+    name: String,
+    mode: InodeMode,
+    created: Mutex<Option<Arc<NfsInode>>>,
+}
+
+impl PendingSymlink {
+    fn created(&self) -> Option<Arc<NfsInode>> {
+        self.created.lock().clone()
+    }
+}
+
+impl Inode for PendingSymlink {
+    fn size(&self) -> usize {
+        self.created().map_or(0, |inode| inode.size())
+    }
+
+    fn resize(&self, _new_size: usize) -> Result<()> {
+        return_errno!(Errno::EINVAL)
+    }
+
+    fn metadata(&self) -> Metadata {
+        match self.created() {
+            Some(inode) => inode.metadata(),
+            None => Metadata::new_symlink(0, self.mode, BLOCK_SIZE),
+        }
+    }
+
+    fn ino(&self) -> u64 {
+        self.created().map_or(0, |inode| inode.ino())
+    }
+
+    fn type_(&self) -> InodeType {
+        InodeType::SymLink
+    }
+
+    fn mode(&self) -> Result<InodeMode> {
+        Ok(self.mode)
+    }
+
+    fn set_mode(&self, _mode: InodeMode) -> Result<()> {
+        return_errno_with_message!(Errno::ENOENT, "the symbolic link is not created yet")
+    }
+
+    fn owner(&self) -> Result<Uid> {
+        Ok(self.metadata().uid)
+    }
+
+    fn set_owner(&self, _uid: Uid) -> Result<()> {
+        return_errno_with_message!(Errno::ENOENT, "the symbolic link is not created yet")
+    }
+
+    fn group(&self) -> Result<Gid> {
+        Ok(self.metadata().gid)
+    }
+
+    fn set_group(&self, _gid: Gid) -> Result<()> {
+        return_errno_with_message!(Errno::ENOENT, "the symbolic link is not created yet")
+    }
+
+    fn atime(&self) -> Duration {
+        self.metadata().atime
+    }
+
+    fn set_atime(&self, _time: Duration) {}
+
+    fn mtime(&self) -> Duration {
+        self.metadata().mtime
+    }
+
+    fn set_mtime(&self, _time: Duration) {}
+
+    fn ctime(&self) -> Duration {
+        self.metadata().ctime
+    }
+
+    fn set_ctime(&self, _time: Duration) {}
+
+    fn read_link(&self) -> Result<String> {
+        match self.created() {
+            Some(inode) => inode.read_link(),
+            None => {
+                return_errno_with_message!(Errno::ENOENT, "the symbolic link is not created yet")
+            }
+        }
+    }
+
+    fn write_link(&self, target: &str) -> Result<()> {
+        let mut created = self.created.lock();
+        if created.is_some() {
+            return_errno_with_message!(Errno::EEXIST, "the symbolic link has been created");
+        }
+        *created = Some(self.dir.create_symlink(&self.name, self.mode, target)?);
+        Ok(())
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        self.dir.fs()
+    }
+
+    fn is_dentry_cacheable(&self) -> bool {
+        false
+    }
+}
+
+fn now() -> Duration {
+    MonotonicCoarseClock::get().read_time()
+}
@@ -0,0 +1,126 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! An NFSv3 client, which mounts the directories that are exported by the network servers.
+//!
+//! The client speaks the MOUNT and NFS protocols over SunRPC, which is carried by either UDP
+//! or TCP. The ports are found with the port mapper of the server, unless they are given.
+//!
+//! A directory is mounted with
+//! `mount -t nfs -o vers=3,proto=tcp,nolock 10.0.2.2:/srv/nfs /mnt`. The root file system can
+//! be mounted from a server with `root=/dev/nfs` on the kernel command line, which is useful
+//! for the diskless boards:
+//!
+//! ```text
+//! root=/dev/nfs nfsroot=[<server-ip>:]<root-dir>[,<nfs-options>] ip=dhcp
+//! ```
+//!
+//! The server defaults to the one of the `ip=` option or the DHCP server, and the directory
+//! defaults to the root path that is offered by DHCP. A `%s` in the directory is replaced with
+//! the IP address of the client.
+//!
+//! Reference: <https://www.kernel.org/doc/html/v6.4/admin-guide/nfs/nfsroot.html>.
+//!
+//! # Limitation
+//!
+//! 1. Only NFSv3 and the `AUTH_UNIX` authentication are supported, and the server must be
+//!    given by its IPv4 address.
+//! 2. The names in the directories are cached by the dentries, so the files that are
+//!    created, removed or renamed by other clients may not be seen.
+//! 3. The data is written back when the file is closed or synchronized, page by page and
+//!    synchronously. File locks are not supported.
+
+mod fs;
+mod inode;
+mod proto;
+mod rpc;
+mod xdr;
+
+pub use fs::NfsFs;
+
+use super::{
+    fs_resolver::{FsPath, FsResolver},
+    path::Dentry,
+    rootfs,
+    utils::{InodeMode, InodeType},
+};
+use crate::{net::ipconfig::ip_config, prelude::*};
+
+/// The directory in the initramfs where the network root file system is mounted.
+const SYSROOT: &str = "sysroot";
+
+/// The directories of the initramfs that are bound into the network root file system.
+const BOUND_DIRS: [&str; 2] = ["proc", "dev"];
+
+/// Mounts the root file system from the NFS server, which is described by the `nfsroot=`
+/// option.
+///
+/// The file system is mounted at `/sysroot` of the initramfs, and becomes the root directory
+/// of the processes that are created afterwards. If it cannot be mounted, the system boots
+/// with the initramfs as the root.
+pub fn mount_root(option: Option<&str>) {
+    match do_mount_root(option) {
+        Ok(()) => println!("[kernel] the root file system is mounted from NFS"),
+        Err(err) => error!(
+            "[NFS] cannot mount the root file system: {:?}, use the initramfs instead",
+            err
+        ),
+    }
+}
+
+fn do_mount_root(option: Option<&str>) -> Result<()> {
+    let (source, options) = nfs_root_source(option.unwrap_or(""))?;
+    let fs = NfsFs::mount(&source, options)?;
+
+    let resolver = FsResolver::new();
+    let sysroot = match resolver.root().lookup(SYSROOT) {
+        Ok(dentry) => dentry,
+        Err(err) if err.error() == Errno::ENOENT => resolver.root().new_fs_child(
+            SYSROOT,
+            InodeType::Dir,
+            InodeMode::from_bits_truncate(0o755),
+        )?,
+        Err(err) => return Err(err),
+    };
+    let new_root = Dentry::new_fs_root(sysroot.mount(fs)?);
+
+    for dir in BOUND_DIRS {
+        let Ok(target) = new_root.lookup(dir) else {
+            warn!("[NFS] /{} does not exist in the root file system", dir);
+            continue;
+        };
+        let source = resolver.lookup(&FsPath::try_from(alloc::format!("/{}", dir).as_str())?)?;
+        source.bind_mount_to(&target, true)?;
+    }
+
+    rootfs::switch_root(new_root);
+    Ok(())
+}
+
+/// Returns the source of the root file system, like `10.0.2.2:/srv/nfs`, and the mount
+/// options.
+fn nfs_root_source(option: &str) -> Result<(String, &str)> {
+    let (path, options) = option.split_once(',').unwrap_or((option, ""));
+    let config = ip_config();
+
+    let path = match path {
+        "" => config
+            .and_then(|config| config.root_path.as_deref())
+            .unwrap_or("/tftpboot/%s"),
+        path => path,
+    };
+    let (server, path) = match path.split_once(':') {
+        Some((server, path)) => (server.to_string(), path),
+        None => {
+            let Some(server) = config.and_then(|config| config.server) else {
+                return_errno_with_message!(Errno::EINVAL, "the NFS server is not specified");
+            };
+            (server.to_string(), path)
+        }
+    };
+    let path = match config {
+        Some(config) => path.replace("%s", &config.addr.to_string()),
+        None => path.to_string(),
+    };
+
+    Ok((alloc::format!("{}:{}", server, path), options))
+}
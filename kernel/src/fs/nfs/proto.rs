@@ -0,0 +1,313 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The data types of the NFSv3 and MOUNTv3 protocols.
+//!
+//! Reference: <https://datatracker.ietf.org/doc/html/rfc1813>.
+
+use core::time::Duration;
+
+use super::xdr::{XdrReader, XdrWriter};
+use crate::{
+    fs::utils::{InodeMode, InodeType},
+    prelude::*,
+};
+
+pub(super) const NFS_PROGRAM: u32 = 100003;
+pub(super) const NFS_VERSION: u32 = 3;
+pub(super) const NFS_PORT: u16 = 2049;
+
+pub(super) const MOUNT_PROGRAM: u32 = 100005;
+pub(super) const MOUNT_VERSION: u32 = 3;
+
+pub(super) const MOUNTPROC3_MNT: u32 = 1;
+
+pub(super) const NFSPROC3_GETATTR: u32 = 1;
+pub(super) const NFSPROC3_SETATTR: u32 = 2;
+pub(super) const NFSPROC3_LOOKUP: u32 = 3;
+pub(super) const NFSPROC3_READLINK: u32 = 5;
+pub(super) const NFSPROC3_READ: u32 = 6;
+pub(super) const NFSPROC3_WRITE: u32 = 7;
+pub(super) const NFSPROC3_CREATE: u32 = 8;
+pub(super) const NFSPROC3_MKDIR: u32 = 9;
+pub(super) const NFSPROC3_SYMLINK: u32 = 10;
+pub(super) const NFSPROC3_MKNOD: u32 = 11;
+pub(super) const NFSPROC3_REMOVE: u32 = 12;
+pub(super) const NFSPROC3_RMDIR: u32 = 13;
+pub(super) const NFSPROC3_RENAME: u32 = 14;
+pub(super) const NFSPROC3_LINK: u32 = 15;
+pub(super) const NFSPROC3_READDIRPLUS: u32 = 17;
+pub(super) const NFSPROC3_FSSTAT: u32 = 18;
+
+/// The largest size of the file handles.
+const NFS3_FHSIZE: usize = 64;
+/// The largest size of the paths, which bounds the names and the targets of symbolic links.
+pub(super) const MAX_PATH_LEN: usize = 4096;
+
+// The ways to create a file (`createmode3`).
+pub(super) const CREATE_GUARDED: u32 = 1;
+
+// The stability of the written data (`stable_how`).
+pub(super) const FILE_SYNC: u32 = 2;
+
+/// A file handle, which identifies a file on the server.
+#[derive(Clone, PartialEq, Eq)]
+pub(super) struct FileHandle(Vec<u8>);
+
+impl FileHandle {
+    pub fn decode(reader: &mut XdrReader) -> Result<Self> {
+        Ok(Self(reader.opaque(NFS3_FHSIZE)?.to_vec()))
+    }
+
+    /// Decodes an optional file handle (`post_op_fh3`).
+    pub fn decode_optional(reader: &mut XdrReader) -> Result<Option<Self>> {
+        if reader.bool()? {
+            Ok(Some(Self::decode(reader)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn encode(&self, writer: &mut XdrWriter) {
+        writer.opaque(&self.0);
+    }
+}
+
+impl Debug for FileHandle {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "FileHandle(")?;
+        for byte in self.0.iter() {
+            write!(f, "{:02x}", byte)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Returns the file type (`ftype3`) of the inode type.
+pub(super) fn ftype(type_: InodeType) -> u32 {
+    match type_ {
+        InodeType::File => 1,
+        InodeType::Dir => 2,
+        InodeType::BlockDevice => 3,
+        InodeType::CharDevice => 4,
+        InodeType::SymLink => 5,
+        InodeType::Socket => 6,
+        InodeType::NamedPipe => 7,
+    }
+}
+
+/// The attributes of a file (`fattr3`).
+#[derive(Debug, Clone)]
+pub(super) struct Fattr {
+    pub type_: InodeType,
+    pub mode: InodeMode,
+    pub nlink: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: u64,
+    pub used: u64,
+    pub rdev: (u32, u32),
+    pub fileid: u64,
+    pub atime: Duration,
+    pub mtime: Duration,
+    pub ctime: Duration,
+}
+
+impl Fattr {
+    pub fn decode(reader: &mut XdrReader) -> Result<Self> {
+        let type_ = match reader.u32()? {
+            1 => InodeType::File,
+            2 => InodeType::Dir,
+            3 => InodeType::BlockDevice,
+            4 => InodeType::CharDevice,
+            5 => InodeType::SymLink,
+            6 => InodeType::Socket,
+            7 => InodeType::NamedPipe,
+            _ => return_errno_with_message!(Errno::EIO, "invalid file type"),
+        };
+        let mode = InodeMode::from_bits_truncate(reader.u32()? as u16);
+        let nlink = reader.u32()?;
+        let uid = reader.u32()?;
+        let gid = reader.u32()?;
+        let size = reader.u64()?;
+        let used = reader.u64()?;
+        let rdev = (reader.u32()?, reader.u32()?);
+        // All the files are regarded as in the exported file system.
+        let _fsid = reader.u64()?;
+        Ok(Self {
+            type_,
+            mode,
+            nlink,
+            uid,
+            gid,
+            size,
+            used,
+            rdev,
+            fileid: reader.u64()?,
+            atime: decode_time(reader)?,
+            mtime: decode_time(reader)?,
+            ctime: decode_time(reader)?,
+        })
+    }
+
+    /// Decodes optional attributes (`post_op_attr`).
+    pub fn decode_optional(reader: &mut XdrReader) -> Result<Option<Self>> {
+        if reader.bool()? {
+            Ok(Some(Self::decode(reader)?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// Decodes the attributes of a directory before and after an operation (`wcc_data`), and
+/// returns the latter.
+pub(super) fn decode_wcc_data(reader: &mut XdrReader) -> Result<Option<Fattr>> {
+    if reader.bool()? {
+        // The size, the modification time and the change time before the operation.
+        let _size = reader.u64()?;
+        let _mtime = decode_time(reader)?;
+        let _ctime = decode_time(reader)?;
+    }
+    Fattr::decode_optional(reader)
+}
+
+fn decode_time(reader: &mut XdrReader) -> Result<Duration> {
+    let secs = reader.u32()?;
+    let nsecs = reader.u32()?;
+    Ok(Duration::new(secs as u64, nsecs.min(999_999_999)))
+}
+
+/// The attributes to set (`sattr3`).
+#[derive(Debug, Default, Clone, Copy)]
+pub(super) struct Sattr {
+    pub mode: Option<InodeMode>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    pub size: Option<u64>,
+    pub atime: Option<Duration>,
+    pub mtime: Option<Duration>,
+}
+
+impl Sattr {
+    pub fn with_mode(mode: InodeMode) -> Self {
+        Self {
+            mode: Some(mode),
+            ..Default::default()
+        }
+    }
+
+    pub fn encode(&self, writer: &mut XdrWriter) {
+        fn encode_u32(writer: &mut XdrWriter, value: Option<u32>) {
+            writer.bool(value.is_some());
+            if let Some(value) = value {
+                writer.u32(value);
+            }
+        }
+        // `SET_TO_CLIENT_TIME` is 2, while `DONT_CHANGE` is 0.
+        fn encode_time(writer: &mut XdrWriter, time: Option<Duration>) {
+            match time {
+                Some(time) => {
+                    writer
+                        .u32(2)
+                        .u32(time.as_secs() as u32)
+                        .u32(time.subsec_nanos());
+                }
+                None => {
+                    writer.u32(0);
+                }
+            }
+        }
+
+        encode_u32(writer, self.mode.map(|mode| mode.bits() as u32));
+        encode_u32(writer, self.uid);
+        encode_u32(writer, self.gid);
+        writer.bool(self.size.is_some());
+        if let Some(size) = self.size {
+            writer.u64(size);
+        }
+        encode_time(writer, self.atime);
+        encode_time(writer, self.mtime);
+    }
+}
+
+/// The file system statistics (the results of `FSSTAT`).
+#[derive(Debug, Clone, Copy)]
+pub(super) struct FsStat {
+    pub total_bytes: u64,
+    pub free_bytes: u64,
+    pub avail_bytes: u64,
+    pub total_files: u64,
+    pub free_files: u64,
+}
+
+impl FsStat {
+    pub fn decode(reader: &mut XdrReader) -> Result<Self> {
+        let _attr = Fattr::decode_optional(reader)?;
+        let stat = Self {
+            total_bytes: reader.u64()?,
+            free_bytes: reader.u64()?,
+            avail_bytes: reader.u64()?,
+            total_files: reader.u64()?,
+            free_files: reader.u64()?,
+        };
+        let _avail_files = reader.u64()?;
+        let _invarsec = reader.u32()?;
+        Ok(stat)
+    }
+}
+
+/// Reads the status of an NFSv3 reply (`nfsstat3`), and converts the failures to the errors.
+pub(super) fn check_status(reader: &mut XdrReader) -> Result<()> {
+    let errno = match reader.u32()? {
+        0 => return Ok(()),
+        1 => Errno::EPERM,
+        2 => Errno::ENOENT,
+        5 => Errno::EIO,
+        6 => Errno::ENXIO,
+        13 => Errno::EACCES,
+        17 => Errno::EEXIST,
+        18 => Errno::EXDEV,
+        19 => Errno::ENODEV,
+        20 => Errno::ENOTDIR,
+        21 => Errno::EISDIR,
+        22 => Errno::EINVAL,
+        27 => Errno::EFBIG,
+        28 => Errno::ENOSPC,
+        30 => Errno::EROFS,
+        31 => Errno::EMLINK,
+        63 => Errno::ENAMETOOLONG,
+        66 => Errno::ENOTEMPTY,
+        69 => Errno::EDQUOT,
+        70 => Errno::ESTALE,
+        71 => Errno::EREMOTE,
+        // `NFS3ERR_BADHANDLE`
+        10001 => Errno::ESTALE,
+        // `NFS3ERR_NOTSUPP`
+        10004 => Errno::EOPNOTSUPP,
+        // `NFS3ERR_TOOSMALL`
+        10005 => Errno::EINVAL,
+        // `NFS3ERR_BADTYPE`
+        10007 => Errno::EINVAL,
+        // `NFS3ERR_JUKEBOX`
+        10008 => Errno::EAGAIN,
+        _ => Errno::EIO,
+    };
+    return_errno_with_message!(errno, "the NFS server returns an error");
+}
+
+/// Reads the status of a MOUNTv3 reply (`mountstat3`).
+pub(super) fn check_mount_status(reader: &mut XdrReader) -> Result<()> {
+    let errno = match reader.u32()? {
+        0 => return Ok(()),
+        1 => Errno::EPERM,
+        2 => Errno::ENOENT,
+        5 => Errno::EIO,
+        13 => Errno::EACCES,
+        20 => Errno::ENOTDIR,
+        22 => Errno::EINVAL,
+        63 => Errno::ENAMETOOLONG,
+        10004 => Errno::EOPNOTSUPP,
+        _ => Errno::EIO,
+    };
+    return_errno_with_message!(errno, "the server refuses to export the directory");
+}
@@ -0,0 +1,428 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The ONC RPC (SunRPC) client, which carries the MOUNT and NFS protocols.
+//!
+//! Calls are sent over the in-kernel UDP and TCP sockets. Over UDP, a call is retransmitted
+//! if no reply is received in time. Over TCP, the messages are delimited by the record marks.
+//!
+//! Reference: <https://datatracker.ietf.org/doc/html/rfc5531>.
+
+use core::{
+    sync::atomic::{AtomicU32, Ordering},
+    time::Duration,
+};
+
+use aster_bigtcp::wire::Ipv4Address;
+
+use super::xdr::{XdrReader, XdrWriter};
+use crate::{
+    events::IoEvents,
+    net::socket::{
+        ip::{datagram::DatagramSocket, get_ephemeral_iface, stream::StreamSocket, IpFamily},
+        MessageHeader, SendRecvFlags, Socket, SocketAddr,
+    },
+    prelude::*,
+    process::signal::Pollable,
+};
+
+const RPC_VERSION: u32 = 2;
+
+const MSG_CALL: u32 = 0;
+const MSG_REPLY: u32 = 1;
+
+const MSG_ACCEPTED: u32 = 0;
+
+const SUCCESS: u32 = 0;
+const PROG_UNAVAIL: u32 = 1;
+const PROG_MISMATCH: u32 = 2;
+const PROC_UNAVAIL: u32 = 3;
+
+const AUTH_NONE: u32 = 0;
+const AUTH_UNIX: u32 = 1;
+
+/// The largest size of the authentication bodies.
+const MAX_AUTH_LEN: usize = 400;
+
+/// The bit of the record marks that indicates the last fragment of a record.
+const LAST_FRAGMENT: u32 = 1 << 31;
+
+/// The range of the privileged ports that are used by the clients, like `xprt_min_resvport`
+/// and `xprt_max_resvport` in Linux.
+///
+/// Many servers only accept the requests from the privileged ports by default.
+const RESERVED_PORTS: core::ops::RangeInclusive<u16> = 665..=1023;
+
+const PMAP_PROGRAM: u32 = 100000;
+const PMAP_VERSION: u32 = 2;
+const PMAP_PORT: u16 = 111;
+const PMAPPROC_GETPORT: u32 = 3;
+
+/// The transport protocol of an RPC client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Protocol {
+    Udp,
+    Tcp,
+}
+
+impl Protocol {
+    /// Returns the IP protocol number, which identifies the protocol in the port mapper.
+    fn ip_protocol(self) -> u32 {
+        match self {
+            Protocol::Udp => 17,
+            Protocol::Tcp => 6,
+        }
+    }
+}
+
+/// The parameters of the retransmission.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Timeout {
+    /// The time to wait for a reply before the first retransmission.
+    pub timeo: Duration,
+    /// The number of retransmissions before a call fails.
+    pub retrans: u32,
+}
+
+enum Transport {
+    Udp(Arc<DatagramSocket>),
+    Tcp(Arc<StreamSocket>),
+}
+
+/// A client of an RPC program on a remote server.
+pub(super) struct RpcClient {
+    transport: Transport,
+    program: u32,
+    version: u32,
+    timeout: Timeout,
+    next_xid: AtomicU32,
+    /// Serializes the calls, so that the replies are read by their callers.
+    call_lock: Mutex<()>,
+}
+
+impl RpcClient {
+    /// Connects to the program on the server.
+    pub fn connect(
+        server: Ipv4Address,
+        port: u16,
+        protocol: Protocol,
+        program: u32,
+        version: u32,
+        timeout: Timeout,
+    ) -> Result<Self> {
+        let transport = match protocol {
+            Protocol::Udp => {
+                let socket = DatagramSocket::new(true, IpFamily::V4);
+                bind_reserved_port(socket.as_ref(), server);
+                socket.connect(SocketAddr::IPv4(server, port))?;
+                Transport::Udp(socket)
+            }
+            Protocol::Tcp => {
+                let socket = StreamSocket::new(true, IpFamily::V4);
+                bind_reserved_port(socket.as_ref(), server);
+                connect_stream(&socket, server, port, timeout.timeo * (timeout.retrans + 1))?;
+                Transport::Tcp(socket)
+            }
+        };
+
+        let mut xid = [0u8; 4];
+        crate::util::random::getrandom(&mut xid)?;
+
+        Ok(Self {
+            transport,
+            program,
+            version,
+            timeout,
+            next_xid: AtomicU32::new(u32::from_ne_bytes(xid)),
+            call_lock: Mutex::new(()),
+        })
+    }
+
+    /// Calls the procedure with the encoded arguments, and returns the encoded results.
+    pub fn call(&self, procedure: u32, args: &[u8]) -> Result<Vec<u8>> {
+        let xid = self.next_xid.fetch_add(1, Ordering::Relaxed);
+        let message = self.build_call(xid, procedure, args);
+
+        let _guard = self.call_lock.lock();
+        let reply = match &self.transport {
+            Transport::Udp(socket) => self.call_udp(socket, xid, &message)?,
+            Transport::Tcp(socket) => self.call_tcp(socket, xid, &message)?,
+        };
+        parse_reply(&reply)
+    }
+
+    fn build_call(&self, xid: u32, procedure: u32, args: &[u8]) -> Vec<u8> {
+        let (uid, gid, groups) = caller_ids();
+
+        let mut cred = XdrWriter::new();
+        cred.u32(0).string("asterinas").u32(uid).u32(gid);
+        cred.u32(groups.len() as u32);
+        for gid in groups {
+            cred.u32(gid);
+        }
+        let cred = cred.into_bytes();
+
+        let mut message = XdrWriter::new();
+        message
+            .u32(xid)
+            .u32(MSG_CALL)
+            .u32(RPC_VERSION)
+            .u32(self.program)
+            .u32(self.version)
+            .u32(procedure)
+            .u32(AUTH_UNIX)
+            .opaque(&cred)
+            .u32(AUTH_NONE)
+            .opaque(&[])
+            .fixed_opaque(args);
+        message.into_bytes()
+    }
+
+    fn call_udp(&self, socket: &Arc<DatagramSocket>, xid: u32, message: &[u8]) -> Result<Vec<u8>> {
+        let mut buf = vec![0u8; MAX_REPLY_LEN];
+        let mut timeo = self.timeout.timeo;
+
+        for _ in 0..=self.timeout.retrans {
+            send_all(socket.as_ref(), message)?;
+
+            let result = socket.wait_events(IoEvents::IN, Some(&timeo), || {
+                let len = recv_some(socket.as_ref(), &mut buf)?;
+                // Drop the stale replies of the calls that are retransmitted.
+                if reply_xid(&buf[..len]) != Some(xid) {
+                    return_errno_with_message!(Errno::EAGAIN, "the reply is not for this call");
+                }
+                Ok(len)
+            });
+            match result {
+                Ok(len) => {
+                    buf.truncate(len);
+                    return Ok(buf);
+                }
+                Err(err) if err.error() == Errno::ETIME => {
+                    debug!("[NFS] RPC call {:#x} timed out, retransmitting", xid);
+                    timeo = (timeo * 2).min(MAX_UDP_TIMEO);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        warn!("[NFS] the server is not responding");
+        return_errno_with_message!(Errno::EIO, "the server is not responding");
+    }
+
+    fn call_tcp(&self, socket: &Arc<StreamSocket>, xid: u32, message: &[u8]) -> Result<Vec<u8>> {
+        let mut record = Vec::with_capacity(message.len() + 4);
+        record.extend_from_slice(&(LAST_FRAGMENT | message.len() as u32).to_be_bytes());
+        record.extend_from_slice(message);
+
+        let timeout = self.timeout.timeo * (self.timeout.retrans + 1);
+        let mut offset = 0;
+        while offset < record.len() {
+            offset += socket.wait_events(IoEvents::OUT, Some(&timeout), || {
+                send_all(socket.as_ref(), &record[offset..])
+            })?;
+        }
+
+        // Skip the replies of the calls that have been interrupted.
+        loop {
+            let reply = recv_record(socket, &timeout)?;
+            if reply_xid(&reply) == Some(xid) {
+                return Ok(reply);
+            }
+        }
+    }
+}
+
+/// The largest size of the replies over UDP, which is enough for the largest reads.
+const MAX_REPLY_LEN: usize = 64 * 1024;
+/// The largest size of the records over TCP.
+const MAX_RECORD_LEN: usize = 1024 * 1024 + 4096;
+/// The largest timeout of the retransmissions over UDP.
+const MAX_UDP_TIMEO: Duration = Duration::from_secs(60);
+
+/// Asks the port mapper of the server for the port of the program.
+pub(super) fn getport(
+    server: Ipv4Address,
+    program: u32,
+    version: u32,
+    protocol: Protocol,
+    timeout: Timeout,
+) -> Result<u16> {
+    let client = RpcClient::connect(
+        server,
+        PMAP_PORT,
+        Protocol::Udp,
+        PMAP_PROGRAM,
+        PMAP_VERSION,
+        timeout,
+    )?;
+
+    let mut args = XdrWriter::new();
+    args.u32(program)
+        .u32(version)
+        .u32(protocol.ip_protocol())
+        .u32(0);
+    let reply = client.call(PMAPPROC_GETPORT, &args.into_bytes())?;
+
+    let port = XdrReader::new(&reply).u32()?;
+    if port == 0 || port > u16::MAX as u32 {
+        return_errno_with_message!(
+            Errno::EPROTONOSUPPORT,
+            "the program is not registered on the server"
+        );
+    }
+    Ok(port as u16)
+}
+
+/// Binds the socket to a privileged port, or leaves it unbound if no such port is free.
+fn bind_reserved_port(socket: &dyn Socket, server: Ipv4Address) {
+    let Some(local_addr) = get_ephemeral_iface(&server).ipv4_addr() else {
+        return;
+    };
+
+    for port in RESERVED_PORTS.rev() {
+        match socket.bind(SocketAddr::IPv4(local_addr, port)) {
+            Ok(()) => return,
+            Err(err) if err.error() == Errno::EADDRINUSE => continue,
+            Err(_) => return,
+        }
+    }
+}
+
+fn connect_stream(
+    socket: &Arc<StreamSocket>,
+    server: Ipv4Address,
+    port: u16,
+    timeout: Duration,
+) -> Result<()> {
+    match socket.connect(SocketAddr::IPv4(server, port)) {
+        Err(err) if err.error() == Errno::EINPROGRESS => (),
+        result => return result,
+    }
+
+    socket.wait_events(
+        IoEvents::OUT | IoEvents::ERR,
+        Some(&timeout),
+        || match socket.connect(SocketAddr::IPv4(server, port)) {
+            Err(err) if err.error() == Errno::EALREADY => {
+                return_errno_with_message!(Errno::EAGAIN, "the connection is pending")
+            }
+            Err(err) if err.error() == Errno::EISCONN => Ok(()),
+            result => result,
+        },
+    )
+}
+
+fn send_all(socket: &dyn Socket, bytes: &[u8]) -> Result<usize> {
+    let mut reader = VmReader::from(bytes).to_fallible();
+    socket.sendmsg(
+        &mut reader,
+        MessageHeader::new(None, Vec::new()),
+        SendRecvFlags::empty(),
+    )
+}
+
+fn recv_some(socket: &dyn Socket, buf: &mut [u8]) -> Result<usize> {
+    let mut writer = VmWriter::from(buf).to_fallible();
+    let (len, _) = socket.recvmsg(&mut writer, SendRecvFlags::empty())?;
+    Ok(len)
+}
+
+/// Receives the fragments of a record over TCP.
+fn recv_record(socket: &Arc<StreamSocket>, timeout: &Duration) -> Result<Vec<u8>> {
+    let mut record = Vec::new();
+    loop {
+        let mut mark = [0u8; 4];
+        recv_exact(socket, &mut mark, timeout)?;
+        let mark = u32::from_be_bytes(mark);
+
+        let len = (mark & !LAST_FRAGMENT) as usize;
+        if record.len() + len > MAX_RECORD_LEN {
+            return_errno_with_message!(Errno::EIO, "the RPC record is too large");
+        }
+        let start = record.len();
+        record.resize(start + len, 0);
+        recv_exact(socket, &mut record[start..], timeout)?;
+
+        if mark & LAST_FRAGMENT != 0 {
+            return Ok(record);
+        }
+    }
+}
+
+fn recv_exact(socket: &Arc<StreamSocket>, buf: &mut [u8], timeout: &Duration) -> Result<()> {
+    let mut offset = 0;
+    while offset < buf.len() {
+        let len = socket.wait_events(IoEvents::IN, Some(timeout), || {
+            recv_some(socket.as_ref(), &mut buf[offset..])
+        })?;
+        if len == 0 {
+            return_errno_with_message!(Errno::EIO, "the server closes the connection");
+        }
+        offset += len;
+    }
+    Ok(())
+}
+
+fn reply_xid(reply: &[u8]) -> Option<u32> {
+    let mut reader = XdrReader::new(reply);
+    let xid = reader.u32().ok()?;
+    (reader.u32().ok()? == MSG_REPLY).then_some(xid)
+}
+
+/// Checks the header of a reply, and returns the encoded results.
+fn parse_reply(reply: &[u8]) -> Result<Vec<u8>> {
+    let mut reader = XdrReader::new(reply);
+    let _xid = reader.u32()?;
+    let _msg_type = reader.u32()?;
+
+    if reader.u32()? != MSG_ACCEPTED {
+        return_errno_with_message!(Errno::EACCES, "the server denies the RPC call");
+    }
+    let _verf_flavor = reader.u32()?;
+    let _verf_body = reader.opaque(MAX_AUTH_LEN)?;
+
+    match reader.u32()? {
+        SUCCESS => Ok(reader.remaining().to_vec()),
+        PROG_UNAVAIL | PROG_MISMATCH => {
+            return_errno_with_message!(
+                Errno::EPROTONOSUPPORT,
+                "the program or the version is not supported by the server"
+            )
+        }
+        PROC_UNAVAIL => {
+            return_errno_with_message!(
+                Errno::EOPNOTSUPP,
+                "the procedure is not supported by the server"
+            )
+        }
+        _ => return_errno_with_message!(Errno::EIO, "the server cannot decode the RPC call"),
+    }
+}
+
+/// Returns the file system IDs of the caller, which are sent as the `AUTH_UNIX` credential.
+///
+/// The kernel threads (e.g., the one that mounts the root file system) act as root.
+fn caller_ids() -> (u32, u32, Vec<u32>) {
+    use crate::{process::posix_thread::AsPosixThread, thread::Thread};
+
+    let Some(thread) = Thread::current() else {
+        return (0, 0, Vec::new());
+    };
+    let Some(posix_thread) = thread.as_posix_thread() else {
+        return (0, 0, Vec::new());
+    };
+
+    let credentials = posix_thread.credentials();
+    // At most 16 supplementary groups can be sent.
+    let groups = credentials
+        .groups()
+        .iter()
+        .take(16)
+        .map(|gid| u32::from(*gid))
+        .collect();
+    (
+        u32::from(credentials.fsuid()),
+        u32::from(credentials.fsgid()),
+        groups,
+    )
+}
@@ -0,0 +1,123 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The External Data Representation (XDR), which encodes the RPC messages.
+//!
+//! Reference: <https://datatracker.ietf.org/doc/html/rfc4506>.
+
+use crate::prelude::*;
+
+/// An encoder that appends the XDR items to a buffer.
+pub(super) struct XdrWriter {
+    buf: Vec<u8>,
+}
+
+impl XdrWriter {
+    pub fn new() -> Self {
+        Self { buf: Vec::new() }
+    }
+
+    pub fn u32(&mut self, value: u32) -> &mut Self {
+        self.buf.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    pub fn u64(&mut self, value: u64) -> &mut Self {
+        self.buf.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    pub fn bool(&mut self, value: bool) -> &mut Self {
+        self.u32(value as u32)
+    }
+
+    /// Writes a fixed-length opaque, whose length is known by both sides.
+    pub fn fixed_opaque(&mut self, bytes: &[u8]) -> &mut Self {
+        self.buf.extend_from_slice(bytes);
+        self.pad();
+        self
+    }
+
+    /// Writes a variable-length opaque, which is prefixed with its length.
+    pub fn opaque(&mut self, bytes: &[u8]) -> &mut Self {
+        self.u32(bytes.len() as u32);
+        self.fixed_opaque(bytes)
+    }
+
+    pub fn string(&mut self, s: &str) -> &mut Self {
+        self.opaque(s.as_bytes())
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+
+    fn pad(&mut self) {
+        let padded_len = self.buf.len().next_multiple_of(4);
+        self.buf.resize(padded_len, 0);
+    }
+}
+
+/// A decoder that reads the XDR items from a buffer.
+///
+/// All the methods fail with `EIO` if the buffer is truncated, since it means that the
+/// server sends a malformed reply.
+pub(super) struct XdrReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> XdrReader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    pub fn u32(&mut self) -> Result<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes(bytes.try_into().unwrap()))
+    }
+
+    pub fn u64(&mut self) -> Result<u64> {
+        let bytes = self.take(8)?;
+        Ok(u64::from_be_bytes(bytes.try_into().unwrap()))
+    }
+
+    pub fn bool(&mut self) -> Result<bool> {
+        Ok(self.u32()? != 0)
+    }
+
+    pub fn fixed_opaque(&mut self, len: usize) -> Result<&'a [u8]> {
+        let bytes = self.take(len)?;
+        self.take(len.next_multiple_of(4) - len)?;
+        Ok(bytes)
+    }
+
+    /// Reads a variable-length opaque, which is at most `max_len` bytes.
+    pub fn opaque(&mut self, max_len: usize) -> Result<&'a [u8]> {
+        let len = self.u32()? as usize;
+        if len > max_len {
+            return_errno_with_message!(Errno::EIO, "the opaque data is too long");
+        }
+        self.fixed_opaque(len)
+    }
+
+    pub fn string(&mut self, max_len: usize) -> Result<String> {
+        let bytes = self.opaque(max_len)?;
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| Error::with_message(Errno::EIO, "the string is not valid UTF-8"))
+    }
+
+    /// Skips the remaining bytes, and returns them.
+    pub fn remaining(&mut self) -> &'a [u8] {
+        let bytes = &self.buf[self.pos..];
+        self.pos = self.buf.len();
+        bytes
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let Some(bytes) = self.buf.get(self.pos..self.pos + len) else {
+            return_errno_with_message!(Errno::EIO, "the XDR data is truncated");
+        };
+        self.pos += len;
+        Ok(bytes)
+    }
+}
@@ -0,0 +1,228 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::format;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use super::inode::OverlayInode;
+use crate::{
+    fs::utils::{FileSystem, FsFlags, Inode, InodeType, SuperBlock, NAME_MAX},
+    prelude::*,
+};
+
+/// The magic number of overlay file systems reported by `statfs`, i.e., `OVERLAYFS_SUPER_MAGIC`.
+const OVERLAY_MAGIC: u64 = 0x794c_7630;
+
+pub struct OverlayFs {
+    /// The root directory of the upper layer.
+    upper: Option<Arc<dyn Inode>>,
+    /// The directory where the copy-ups are staged, which is on the same file system
+    /// as the upper layer.
+    work: Option<Arc<dyn Inode>>,
+    /// The root directories of the lower layers, from top to bottom.
+    lowers: Vec<Arc<dyn Inode>>,
+    root: Arc<OverlayInode>,
+    /// Used for the names of the staged files.
+    next_work_id: AtomicU64,
+    /// A global lock, which must be held before looking up or modifying any directory.
+    mutex: Mutex<()>,
+}
+
+impl OverlayFs {
+    /// Creates an overlay file system from the root directories of the layers.
+    ///
+    /// The upper layer and the work directory must be both given or both omitted.
+    pub fn new(
+        lowers: Vec<Arc<dyn Inode>>,
+        upper: Option<Arc<dyn Inode>>,
+        work: Option<Arc<dyn Inode>>,
+    ) -> Result<Arc<Self>> {
+        if lowers.is_empty() {
+            return_errno_with_message!(Errno::EINVAL, "no lower layers");
+        }
+        let mut layers = lowers.iter().chain(upper.iter()).chain(work.iter());
+        if layers.any(|dir| dir.type_() != InodeType::Dir) {
+            return_errno_with_message!(Errno::ENOTDIR, "a layer is not a directory");
+        }
+        match (&upper, &work) {
+            (Some(upper), Some(work)) => {
+                if !Arc::ptr_eq(&upper.fs(), &work.fs()) {
+                    return_errno_with_message!(
+                        Errno::EXDEV,
+                        "the upper layer and the work directory are on different file systems"
+                    );
+                }
+            }
+            (None, None) => {}
+            _ => return_errno_with_message!(
+                Errno::EINVAL,
+                "the upper layer and the work directory must be used together"
+            ),
+        }
+
+        let fs = Arc::new_cyclic(|weak_fs| Self {
+            root: OverlayInode::new_root(weak_fs.clone(), upper.clone(), lowers.clone()),
+            upper,
+            work,
+            lowers,
+            next_work_id: AtomicU64::new(0),
+            mutex: Mutex::new(()),
+        });
+        Ok(fs)
+    }
+
+    /// Returns the work directory, or `None` if the file system is read-only.
+    pub(super) fn work(&self) -> Option<&Arc<dyn Inode>> {
+        self.work.as_ref()
+    }
+
+    pub(super) fn lock(&self) -> MutexGuard<'_, ()> {
+        self.mutex.lock()
+    }
+
+    /// Allocates a name for a file staged in the work directory.
+    pub(super) fn alloc_work_name(&self) -> String {
+        let id = self.next_work_id.fetch_add(1, Ordering::Relaxed);
+        format!("#ovl{:x}", id)
+    }
+}
+
+impl FileSystem for OverlayFs {
+    fn sync(&self) -> Result<()> {
+        if let Some(upper) = self.upper.as_ref() {
+            upper.fs().sync()?;
+        }
+        Ok(())
+    }
+
+    fn root_inode(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+
+    fn sb(&self) -> SuperBlock {
+        let layer = self.upper.as_ref().unwrap_or(&self.lowers[0]);
+        SuperBlock::new(OVERLAY_MAGIC, layer.fs().sb().bsize, NAME_MAX)
+    }
+
+    fn flags(&self) -> FsFlags {
+        FsFlags::empty()
+    }
+}
+
+impl Debug for OverlayFs {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("OverlayFs")
+            .field("num_lowers", &self.lowers.len())
+            .field("has_upper", &self.upper.is_some())
+            .finish()
+    }
+}
+
+/// The mount options of overlayfs, i.e., the paths of the layers.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OverlayMountOptions {
+    /// The paths of the lower layers, from top to bottom.
+    pub lowerdir: Vec<String>,
+    pub upperdir: Option<String>,
+    pub workdir: Option<String>,
+}
+
+impl OverlayMountOptions {
+    /// Parses the options in the form of `lowerdir=/a:/b,upperdir=/u,workdir=/w`.
+    ///
+    /// The `:` and `,` characters in the paths can be escaped with `\`.
+    pub fn parse(options: &str) -> Result<Self> {
+        let mut mount_options = Self::default();
+        for option in split_escaped(options, ',') {
+            if option.is_empty() {
+                continue;
+            }
+            let (key, value) = option.split_once('=').unwrap_or((option.as_str(), ""));
+            match key {
+                "lowerdir" => {
+                    mount_options.lowerdir = split_escaped(value, ':')
+                        .into_iter()
+                        .map(|path| unescape(&path))
+                        .collect();
+                    if mount_options.lowerdir.iter().any(|path| path.is_empty()) {
+                        return_errno_with_message!(Errno::EINVAL, "empty lowerdir");
+                    }
+                }
+                "upperdir" => mount_options.upperdir = Some(unescape(value)),
+                "workdir" => mount_options.workdir = Some(unescape(value)),
+                _ => {
+                    warn!("unsupported overlayfs mount option: {}", key);
+                    return_errno_with_message!(Errno::EINVAL, "unsupported mount option");
+                }
+            }
+        }
+
+        if mount_options.lowerdir.is_empty() {
+            return_errno_with_message!(Errno::EINVAL, "lowerdir is required");
+        }
+        Ok(mount_options)
+    }
+}
+
+/// Splits the string by the separator which is not escaped, keeping the escapes.
+fn split_escaped(s: &str, separator: char) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut part = String::new();
+    let mut chars = s.chars();
+    while let Some(ch) = chars.next() {
+        if ch == '\\' {
+            part.push(ch);
+            if let Some(next) = chars.next() {
+                part.push(next);
+            }
+        } else if ch == separator {
+            parts.push(core::mem::take(&mut part));
+        } else {
+            part.push(ch);
+        }
+    }
+    parts.push(part);
+    parts
+}
+
+fn unescape(s: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = s.chars();
+    while let Some(ch) = chars.next() {
+        if ch == '\\' {
+            if let Some(next) = chars.next() {
+                unescaped.push(next);
+            }
+        } else {
+            unescaped.push(ch);
+        }
+    }
+    unescaped
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn parse_options() {
+        let options =
+            OverlayMountOptions::parse("lowerdir=/a:/b\\:c,upperdir=/u,workdir=/w").unwrap();
+        assert_eq!(options.lowerdir, vec!["/a".to_string(), "/b:c".to_string()]);
+        assert_eq!(options.upperdir.as_deref(), Some("/u"));
+        assert_eq!(options.workdir.as_deref(), Some("/w"));
+
+        let options = OverlayMountOptions::parse("lowerdir=/a").unwrap();
+        assert_eq!(options.lowerdir, vec!["/a".to_string()]);
+        assert!(options.upperdir.is_none());
+    }
+
+    #[ktest]
+    fn parse_invalid_options() {
+        assert!(OverlayMountOptions::parse("").is_err());
+        assert!(OverlayMountOptions::parse("upperdir=/u,workdir=/w").is_err());
+        assert!(OverlayMountOptions::parse("lowerdir=/a::/b").is_err());
+        assert!(OverlayMountOptions::parse("lowerdir=/a,redirect_dir=on").is_err());
+    }
+}
@@ -0,0 +1,777 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::time::Duration;
+
+use aster_rights::Full;
+
+use super::{
+    fs::OverlayFs,
+    whiteout::{
+        create_whiteout, is_opaque, is_whiteout, origin_ino, remove_whiteout, set_opaque,
+        set_origin_ino,
+    },
+};
+use crate::{
+    events::IoEvents,
+    fs::{
+        device::Device,
+        utils::{
+            DirentVisitor, Extension, FallocMode, FileSystem, Inode, InodeMode, InodeType,
+            IoctlCmd, Metadata, MknodType, XattrName, XattrNamespace, XattrSetFlags,
+        },
+    },
+    prelude::*,
+    process::{signal::PollHandle, Gid, Uid},
+    vm::vmo::Vmo,
+};
+
+/// The size of the buffer used to copy up the data of files.
+const COPY_UP_CHUNK_SIZE: usize = 16 * PAGE_SIZE;
+
+/// The number of cached children, above which the dropped ones are pruned.
+const CHILDREN_PRUNE_THRESHOLD: usize = 64;
+
+/// An inode of the overlay file system, which stands for the inodes of the same path
+/// in the layers.
+pub struct OverlayInode {
+    /// The inode number, which comes from the bottommost layer so that it
+    /// does not change after a copy-up.
+    ino: u64,
+    type_: InodeType,
+    /// The inode in the upper layer, which is set once the inode is copied up.
+    upper: RwMutex<Option<Arc<dyn Inode>>>,
+    /// The inodes in the lower layers, from top to bottom.
+    ///
+    /// Only a merged directory has more than one of them.
+    lowers: Vec<Arc<dyn Inode>>,
+    /// The parent directory and the name, which tell where to copy up the inode.
+    /// This is `None` for the root.
+    location: RwMutex<Option<(Arc<OverlayInode>, String)>>,
+    /// The children that have been looked up.
+    ///
+    /// Looking up the same name returns the same inode as long as it is alive, so
+    /// a copy-up is visible to all the opened files.
+    children: Mutex<BTreeMap<String, Weak<OverlayInode>>>,
+    extension: Extension,
+    fs: Weak<OverlayFs>,
+    weak_self: Weak<Self>,
+}
+
+impl OverlayInode {
+    pub(super) fn new_root(
+        fs: Weak<OverlayFs>,
+        upper: Option<Arc<dyn Inode>>,
+        lowers: Vec<Arc<dyn Inode>>,
+    ) -> Arc<Self> {
+        Self::new(fs, upper, lowers, None)
+    }
+
+    fn new(
+        fs: Weak<OverlayFs>,
+        upper: Option<Arc<dyn Inode>>,
+        lowers: Vec<Arc<dyn Inode>>,
+        location: Option<(Arc<OverlayInode>, String)>,
+    ) -> Arc<Self> {
+        let (type_, ino) = match (upper.as_ref(), lowers.last()) {
+            (_, Some(lowest)) => (lowest.type_(), lowest.ino()),
+            (Some(upper), None) => (
+                upper.type_(),
+                origin_ino(upper).unwrap_or_else(|| upper.ino()),
+            ),
+            (None, None) => unreachable!("an overlay inode must have a layer"),
+        };
+
+        Arc::new_cyclic(|weak_self| Self {
+            ino,
+            type_,
+            upper: RwMutex::new(upper),
+            lowers,
+            location: RwMutex::new(location),
+            children: Mutex::new(BTreeMap::new()),
+            extension: Extension::new(),
+            fs,
+            weak_self: weak_self.clone(),
+        })
+    }
+
+    fn overlay_fs(&self) -> Arc<OverlayFs> {
+        self.fs.upgrade().unwrap()
+    }
+
+    fn upper(&self) -> Option<Arc<dyn Inode>> {
+        self.upper.read().clone()
+    }
+
+    /// Returns the inode of the topmost layer, which serves the reads.
+    fn real(&self) -> Arc<dyn Inode> {
+        match self.upper() {
+            Some(upper) => upper,
+            None => self.lowers[0].clone(),
+        }
+    }
+
+    fn check_dir(&self) -> Result<()> {
+        if self.type_ != InodeType::Dir {
+            return_errno_with_message!(Errno::ENOTDIR, "self is not dir");
+        }
+        Ok(())
+    }
+
+    fn lookup_child(&self, name: &str, _fs_guard: &MutexGuard<'_, ()>) -> Result<Arc<Self>> {
+        if let Some(child) = self.children.lock().get(name).and_then(Weak::upgrade) {
+            return Ok(child);
+        }
+
+        let child = self.lookup_layers(name)?;
+        self.insert_child(name, &child);
+        Ok(child)
+    }
+
+    fn insert_child(&self, name: &str, child: &Arc<Self>) {
+        let mut children = self.children.lock();
+        if children.len() >= CHILDREN_PRUNE_THRESHOLD {
+            children.retain(|_, child| child.strong_count() > 0);
+        }
+        children.insert(name.to_string(), Arc::downgrade(child));
+    }
+
+    /// Looks up the layers from top to bottom.
+    ///
+    /// The search stops at a whiteout, a non-directory or an opaque directory. A name
+    /// stands for a merged directory if it is a directory in several layers.
+    fn lookup_layers(&self, name: &str) -> Result<Arc<Self>> {
+        let mut upper = None;
+        let mut is_merging = true;
+        if let Some(dir) = self.upper() {
+            if let Some(inode) = lookup_in_layer(&dir, name)? {
+                if is_whiteout(&inode) {
+                    return_errno_with_message!(Errno::ENOENT, "the file is whited out");
+                }
+                is_merging = inode.type_() == InodeType::Dir && !is_opaque(&inode);
+                upper = Some(inode);
+            }
+        }
+
+        let mut lowers: Vec<Arc<dyn Inode>> = Vec::new();
+        if is_merging {
+            for dir in self.lowers.iter() {
+                let Some(inode) = lookup_in_layer(dir, name)? else {
+                    continue;
+                };
+                if is_whiteout(&inode) {
+                    break;
+                }
+                let is_dir = inode.type_() == InodeType::Dir;
+                // A non-directory is hidden by the directory above it.
+                let is_topmost = upper.is_none() && lowers.is_empty();
+                if !is_topmost && !is_dir {
+                    break;
+                }
+                let stops = !is_dir || is_opaque(&inode);
+                lowers.push(inode);
+                if stops {
+                    break;
+                }
+            }
+        }
+
+        if upper.is_none() && lowers.is_empty() {
+            return_errno_with_message!(Errno::ENOENT, "the file does not exist");
+        }
+        let location = (self.weak_self.upgrade().unwrap(), name.to_string());
+        Ok(Self::new(self.fs.clone(), upper, lowers, Some(location)))
+    }
+
+    /// Creates the inode of a new entry, which only exists in the upper layer.
+    fn new_child(&self, name: &str, upper: Arc<dyn Inode>) -> Arc<Self> {
+        let location = (self.weak_self.upgrade().unwrap(), name.to_string());
+        let child = Self::new(self.fs.clone(), Some(upper), Vec::new(), Some(location));
+        self.insert_child(name, &child);
+        child
+    }
+
+    /// Copies up the inode if it has not been, after copying up its parent.
+    ///
+    /// The copy is prepared in the work directory and then moved into place, so
+    /// a partially copied file never shows up.
+    fn copy_up(&self, fs_guard: &MutexGuard<'_, ()>) -> Result<Arc<dyn Inode>> {
+        if let Some(upper) = self.upper() {
+            return Ok(upper);
+        }
+
+        let fs = self.overlay_fs();
+        let Some(work) = fs.work() else {
+            return_errno_with_message!(Errno::EROFS, "the overlay has no upper layer");
+        };
+        // The root always has an upper inode if there is an upper layer.
+        let (parent, name) = self.location.read().clone().unwrap();
+        let parent_upper = parent.copy_up(fs_guard)?;
+
+        let (work_name, staged) = loop {
+            let work_name = fs.alloc_work_name();
+            match self.copy_up_to(work, &work_name) {
+                Ok(staged) => break (work_name, staged),
+                Err(err) if err.error() == Errno::EEXIST => continue,
+                Err(err) => return Err(err),
+            }
+        };
+        if let Err(err) = work.rename(&work_name, &parent_upper, &name) {
+            remove_staged(work, &work_name, self.type_);
+            return Err(err);
+        }
+
+        *self.upper.write() = Some(staged.clone());
+        Ok(staged)
+    }
+
+    /// Copies the inode of the topmost lower layer into the work directory.
+    fn copy_up_to(&self, work: &Arc<dyn Inode>, work_name: &str) -> Result<Arc<dyn Inode>> {
+        let lower = &self.lowers[0];
+        let metadata = lower.metadata();
+        let staged = match self.type_ {
+            InodeType::Dir | InodeType::File | InodeType::SymLink => {
+                work.create(work_name, self.type_, metadata.mode)?
+            }
+            InodeType::NamedPipe => {
+                work.mknod(work_name, metadata.mode, MknodType::NamedPipeNode)?
+            }
+            InodeType::CharDevice | InodeType::BlockDevice => {
+                let Some(device) = lower.as_device() else {
+                    return_errno_with_message!(Errno::EPERM, "the device cannot be copied up");
+                };
+                work.mknod(work_name, metadata.mode, MknodType::from(device))?
+            }
+            InodeType::Socket => {
+                return_errno_with_message!(Errno::EPERM, "sockets cannot be copied up")
+            }
+        };
+
+        if let Err(err) = self.copy_contents(lower, &staged, &metadata) {
+            remove_staged(work, work_name, self.type_);
+            return Err(err);
+        }
+        Ok(staged)
+    }
+
+    fn copy_contents(
+        &self,
+        lower: &Arc<dyn Inode>,
+        staged: &Arc<dyn Inode>,
+        metadata: &Metadata,
+    ) -> Result<()> {
+        match self.type_ {
+            InodeType::File => {
+                let mut buf = vec![0u8; COPY_UP_CHUNK_SIZE];
+                let mut offset = 0;
+                while offset < metadata.size {
+                    let read_len = lower.read_bytes_at(offset, &mut buf)?;
+                    if read_len == 0 {
+                        break;
+                    }
+                    staged.write_bytes_at(offset, &buf[..read_len])?;
+                    offset += read_len;
+                }
+            }
+            InodeType::SymLink => staged.write_link(&lower.read_link()?)?,
+            _ => {}
+        }
+
+        staged.set_mode(metadata.mode)?;
+        staged.set_owner(metadata.uid)?;
+        staged.set_group(metadata.gid)?;
+        staged.set_atime(metadata.atime);
+        staged.set_mtime(metadata.mtime);
+        if self.type_ != InodeType::Dir {
+            set_origin_ino(staged, self.ino);
+        }
+        Ok(())
+    }
+
+    fn copy_up_locked(&self) -> Result<Arc<dyn Inode>> {
+        if let Some(upper) = self.upper() {
+            return Ok(upper);
+        }
+        let fs = self.overlay_fs();
+        let fs_guard = fs.lock();
+        self.copy_up(&fs_guard)
+    }
+
+    /// Checks that `name` is absent, and prepares the upper directory to add it.
+    ///
+    /// Returns the upper directory and whether a whiteout was removed.
+    fn prepare_new_entry(
+        &self,
+        name: &str,
+        fs_guard: &MutexGuard<'_, ()>,
+    ) -> Result<(Arc<dyn Inode>, bool)> {
+        match self.lookup_child(name, fs_guard) {
+            Ok(_) => return_errno_with_message!(Errno::EEXIST, "entry exist"),
+            Err(err) if err.error() == Errno::ENOENT => {}
+            Err(err) => return Err(err),
+        }
+
+        let upper = self.copy_up(fs_guard)?;
+        let whited_out = remove_whiteout(&upper, name)?;
+        Ok((upper, whited_out))
+    }
+
+    /// Returns the merged entries of the directory. Each item is a tuple of
+    /// the name, the inode number and the type.
+    fn merged_entries(
+        &self,
+        fs_guard: &MutexGuard<'_, ()>,
+    ) -> Result<Vec<(String, u64, InodeType)>> {
+        let mut names = Vec::new();
+        let mut seen_names = BTreeSet::new();
+        let upper = self.upper();
+        for dir in upper.iter().chain(self.lowers.iter()) {
+            let mut layer_names: Vec<String> = Vec::new();
+            dir.readdir_at(0, &mut layer_names)?;
+            for name in layer_names {
+                if name != "." && name != ".." && seen_names.insert(name.clone()) {
+                    names.push(name);
+                }
+            }
+        }
+
+        let parent_ino = match self.location.read().as_ref() {
+            Some((parent, _)) => parent.ino,
+            None => self.ino,
+        };
+        let mut entries = Vec::with_capacity(names.len() + 2);
+        entries.push((".".to_string(), self.ino, InodeType::Dir));
+        entries.push(("..".to_string(), parent_ino, InodeType::Dir));
+        for name in names {
+            // The names that are whited out or hidden are skipped.
+            match self.lookup_child(&name, fs_guard) {
+                Ok(child) => entries.push((name, child.ino, child.type_)),
+                Err(err) if err.error() == Errno::ENOENT => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(entries)
+    }
+
+    fn is_empty_dir(&self, fs_guard: &MutexGuard<'_, ()>) -> Result<bool> {
+        Ok(self.merged_entries(fs_guard)?.len() == 2)
+    }
+
+    /// Removes the whiteouts in the upper directory, which is otherwise empty.
+    fn clear_whiteouts(&self, upper: &Arc<dyn Inode>) -> Result<()> {
+        let mut names: Vec<String> = Vec::new();
+        upper.readdir_at(0, &mut names)?;
+        for name in names.iter().filter(|name| *name != "." && *name != "..") {
+            remove_whiteout(upper, name)?;
+        }
+        Ok(())
+    }
+}
+
+fn lookup_in_layer(dir: &Arc<dyn Inode>, name: &str) -> Result<Option<Arc<dyn Inode>>> {
+    match dir.lookup(name) {
+        Ok(inode) => Ok(Some(inode)),
+        Err(err) if err.error() == Errno::ENOENT => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+fn remove_staged(work: &Arc<dyn Inode>, work_name: &str, type_: InodeType) {
+    let res = if type_ == InodeType::Dir {
+        work.rmdir(work_name)
+    } else {
+        work.unlink(work_name)
+    };
+    if let Err(err) = res {
+        warn!(
+            "failed to remove the staged copy-up {}: {:?}",
+            work_name, err
+        );
+    }
+}
+
+impl Inode for OverlayInode {
+    fn size(&self) -> usize {
+        self.real().size()
+    }
+
+    fn resize(&self, new_size: usize) -> Result<()> {
+        self.copy_up_locked()?.resize(new_size)
+    }
+
+    fn metadata(&self) -> Metadata {
+        let mut metadata = self.real().metadata();
+        metadata.ino = self.ino;
+        metadata
+    }
+
+    fn ino(&self) -> u64 {
+        self.ino
+    }
+
+    fn type_(&self) -> InodeType {
+        self.type_
+    }
+
+    fn mode(&self) -> Result<InodeMode> {
+        self.real().mode()
+    }
+
+    fn set_mode(&self, mode: InodeMode) -> Result<()> {
+        self.copy_up_locked()?.set_mode(mode)
+    }
+
+    fn owner(&self) -> Result<Uid> {
+        self.real().owner()
+    }
+
+    fn set_owner(&self, uid: Uid) -> Result<()> {
+        self.copy_up_locked()?.set_owner(uid)
+    }
+
+    fn group(&self) -> Result<Gid> {
+        self.real().group()
+    }
+
+    fn set_group(&self, gid: Gid) -> Result<()> {
+        self.copy_up_locked()?.set_group(gid)
+    }
+
+    fn atime(&self) -> Duration {
+        self.real().atime()
+    }
+
+    fn set_atime(&self, time: Duration) {
+        // Accessing a lower file does not copy it up.
+        if let Some(upper) = self.upper() {
+            upper.set_atime(time);
+        }
+    }
+
+    fn mtime(&self) -> Duration {
+        self.real().mtime()
+    }
+
+    fn set_mtime(&self, time: Duration) {
+        match self.copy_up_locked() {
+            Ok(upper) => upper.set_mtime(time),
+            Err(err) => debug!("failed to copy up to set mtime: {:?}", err),
+        }
+    }
+
+    fn ctime(&self) -> Duration {
+        self.real().ctime()
+    }
+
+    fn set_ctime(&self, time: Duration) {
+        if let Some(upper) = self.upper() {
+            upper.set_ctime(time);
+        }
+    }
+
+    fn page_cache(&self) -> Option<Vmo<Full>> {
+        self.real().page_cache()
+    }
+
+    fn read_at(&self, offset: usize, writer: &mut VmWriter) -> Result<usize> {
+        self.real().read_at(offset, writer)
+    }
+
+    fn read_direct_at(&self, offset: usize, writer: &mut VmWriter) -> Result<usize> {
+        self.real().read_direct_at(offset, writer)
+    }
+
+    fn write_at(&self, offset: usize, reader: &mut VmReader) -> Result<usize> {
+        self.copy_up_locked()?.write_at(offset, reader)
+    }
+
+    fn write_direct_at(&self, offset: usize, reader: &mut VmReader) -> Result<usize> {
+        self.copy_up_locked()?.write_direct_at(offset, reader)
+    }
+
+    fn create(&self, name: &str, type_: InodeType, mode: InodeMode) -> Result<Arc<dyn Inode>> {
+        self.check_dir()?;
+        let fs = self.overlay_fs();
+        let fs_guard = fs.lock();
+
+        let (upper, whited_out) = self.prepare_new_entry(name, &fs_guard)?;
+        let res = upper.create(name, type_, mode).and_then(|inode| {
+            // A new directory must not be merged with the ones that are whited out.
+            if whited_out && type_ == InodeType::Dir {
+                if let Err(err) = set_opaque(&inode) {
+                    upper.rmdir(name)?;
+                    return Err(err);
+                }
+            }
+            Ok(inode)
+        });
+        match res {
+            Ok(inode) => Ok(self.new_child(name, inode)),
+            Err(err) => {
+                if whited_out {
+                    create_whiteout(&upper, name)?;
+                }
+                Err(err)
+            }
+        }
+    }
+
+    fn mknod(&self, name: &str, mode: InodeMode, type_: MknodType) -> Result<Arc<dyn Inode>> {
+        self.check_dir()?;
+        let fs = self.overlay_fs();
+        let fs_guard = fs.lock();
+
+        let (upper, whited_out) = self.prepare_new_entry(name, &fs_guard)?;
+        match upper.mknod(name, mode, type_) {
+            Ok(inode) => Ok(self.new_child(name, inode)),
+            Err(err) => {
+                if whited_out {
+                    create_whiteout(&upper, name)?;
+                }
+                Err(err)
+            }
+        }
+    }
+
+    fn as_device(&self) -> Option<Arc<dyn Device>> {
+        self.real().as_device()
+    }
+
+    fn readdir_at(&self, offset: usize, visitor: &mut dyn DirentVisitor) -> Result<usize> {
+        self.check_dir()?;
+        let entries = {
+            let fs = self.overlay_fs();
+            let fs_guard = fs.lock();
+            self.merged_entries(&fs_guard)?
+        };
+
+        let mut count = 0;
+        for (idx, (name, ino, type_)) in entries.iter().enumerate().skip(offset) {
+            if let Err(err) = visitor.visit(name, *ino, *type_, idx) {
+                if count == 0 {
+                    return Err(err);
+                }
+                break;
+            }
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    fn link(&self, old: &Arc<dyn Inode>, name: &str) -> Result<()> {
+        self.check_dir()?;
+        if !Arc::ptr_eq(&self.fs(), &old.fs()) {
+            return_errno_with_message!(Errno::EXDEV, "not same fs");
+        }
+        let old = old
+            .downcast_ref::<OverlayInode>()
+            .ok_or(Error::new(Errno::EXDEV))?;
+        if old.type_ == InodeType::Dir {
+            return_errno_with_message!(Errno::EPERM, "old is a dir");
+        }
+        let fs = self.overlay_fs();
+        let fs_guard = fs.lock();
+
+        let old_upper = old.copy_up(&fs_guard)?;
+        let (upper, whited_out) = self.prepare_new_entry(name, &fs_guard)?;
+        if let Err(err) = upper.link(&old_upper, name) {
+            if whited_out {
+                create_whiteout(&upper, name)?;
+            }
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    fn unlink(&self, name: &str) -> Result<()> {
+        self.check_dir()?;
+        let fs = self.overlay_fs();
+        let fs_guard = fs.lock();
+
+        let child = self.lookup_child(name, &fs_guard)?;
+        if child.type_ == InodeType::Dir {
+            return_errno_with_message!(Errno::EISDIR, "unlink on dir");
+        }
+        let upper = self.copy_up(&fs_guard)?;
+        if child.upper().is_some() {
+            upper.unlink(name)?;
+        }
+        if !child.lowers.is_empty() {
+            create_whiteout(&upper, name)?;
+        }
+        self.children.lock().remove(name);
+        Ok(())
+    }
+
+    fn rmdir(&self, name: &str) -> Result<()> {
+        self.check_dir()?;
+        let fs = self.overlay_fs();
+        let fs_guard = fs.lock();
+
+        let child = self.lookup_child(name, &fs_guard)?;
+        if child.type_ != InodeType::Dir {
+            return_errno_with_message!(Errno::ENOTDIR, "rmdir on not dir");
+        }
+        if !child.is_empty_dir(&fs_guard)? {
+            return_errno_with_message!(Errno::ENOTEMPTY, "dir not empty");
+        }
+        let upper = self.copy_up(&fs_guard)?;
+        if let Some(child_upper) = child.upper() {
+            child.clear_whiteouts(&child_upper)?;
+            upper.rmdir(name)?;
+        }
+        if !child.lowers.is_empty() {
+            create_whiteout(&upper, name)?;
+        }
+        self.children.lock().remove(name);
+        Ok(())
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>> {
+        self.check_dir()?;
+        let fs = self.overlay_fs();
+        let fs_guard = fs.lock();
+        let child = self.lookup_child(name, &fs_guard)?;
+        Ok(child)
+    }
+
+    fn rename(&self, old_name: &str, target: &Arc<dyn Inode>, new_name: &str) -> Result<()> {
+        self.check_dir()?;
+        if !Arc::ptr_eq(&self.fs(), &target.fs()) {
+            return_errno_with_message!(Errno::EXDEV, "not same fs");
+        }
+        let target = target
+            .downcast_ref::<OverlayInode>()
+            .ok_or(Error::new(Errno::EXDEV))?;
+        target.check_dir()?;
+        let fs = self.overlay_fs();
+        let fs_guard = fs.lock();
+
+        let src = self.lookup_child(old_name, &fs_guard)?;
+        // Like Linux without `redirect_dir`, the user space is expected to fall back
+        // to copying the directory.
+        if src.type_ == InodeType::Dir && !src.lowers.is_empty() {
+            return_errno_with_message!(Errno::EXDEV, "a merged dir cannot be renamed");
+        }
+        let dst = match target.lookup_child(new_name, &fs_guard) {
+            Ok(dst) => Some(dst),
+            Err(err) if err.error() == Errno::ENOENT => None,
+            Err(err) => return Err(err),
+        };
+        if let Some(dst) = dst.as_ref() {
+            if Arc::ptr_eq(&src, dst) {
+                return Ok(());
+            }
+            if src.type_ == InodeType::Dir {
+                if dst.type_ != InodeType::Dir {
+                    return_errno_with_message!(Errno::ENOTDIR, "dst is not dir");
+                }
+                if !dst.is_empty_dir(&fs_guard)? {
+                    return_errno_with_message!(Errno::ENOTEMPTY, "dst is not empty");
+                }
+            } else if dst.type_ == InodeType::Dir {
+                return_errno_with_message!(Errno::EISDIR, "dst is dir");
+            }
+        }
+
+        let src_upper = src.copy_up(&fs_guard)?;
+        let self_upper = self.copy_up(&fs_guard)?;
+        let target_upper = target.copy_up(&fs_guard)?;
+        let whited_out = remove_whiteout(&target_upper, new_name)?;
+        if let Some(dst) = dst.as_ref() {
+            if let Some(dst_upper) = dst.upper().filter(|_| dst.type_ == InodeType::Dir) {
+                dst.clear_whiteouts(&dst_upper)?;
+            }
+        }
+        self_upper.rename(old_name, &target_upper, new_name)?;
+
+        // The moved directory must not be merged with the ones that it covers.
+        let covers_lowers = whited_out || dst.as_ref().is_some_and(|dst| !dst.lowers.is_empty());
+        if src.type_ == InodeType::Dir && covers_lowers {
+            set_opaque(&src_upper)?;
+        }
+        if !src.lowers.is_empty() {
+            create_whiteout(&self_upper, old_name)?;
+        }
+
+        self.children.lock().remove(old_name);
+        target.children.lock().remove(new_name);
+        let location = (target.weak_self.upgrade().unwrap(), new_name.to_string());
+        *src.location.write() = Some(location);
+        target.insert_child(new_name, &src);
+        Ok(())
+    }
+
+    fn read_link(&self) -> Result<String> {
+        self.real().read_link()
+    }
+
+    fn write_link(&self, target: &str) -> Result<()> {
+        self.copy_up_locked()?.write_link(target)
+    }
+
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        self.real().ioctl(cmd, arg)
+    }
+
+    fn sync_all(&self) -> Result<()> {
+        match self.upper() {
+            Some(upper) => upper.sync_all(),
+            None => Ok(()),
+        }
+    }
+
+    fn sync_data(&self) -> Result<()> {
+        match self.upper() {
+            Some(upper) => upper.sync_data(),
+            None => Ok(()),
+        }
+    }
+
+    fn fallocate(&self, mode: FallocMode, offset: usize, len: usize) -> Result<()> {
+        self.copy_up_locked()?.fallocate(mode, offset, len)
+    }
+
+    fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
+        self.real().poll(mask, poller)
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        self.overlay_fs()
+    }
+
+    fn extension(&self) -> Option<&Extension> {
+        Some(&self.extension)
+    }
+
+    fn set_xattr(
+        &self,
+        name: XattrName,
+        value_reader: &mut VmReader,
+        flags: XattrSetFlags,
+    ) -> Result<()> {
+        self.copy_up_locked()?.set_xattr(name, value_reader, flags)
+    }
+
+    fn get_xattr(&self, name: XattrName, value_writer: &mut VmWriter) -> Result<usize> {
+        self.real().get_xattr(name, value_writer)
+    }
+
+    fn list_xattr(&self, namespace: XattrNamespace, list_writer: &mut VmWriter) -> Result<usize> {
+        self.real().list_xattr(namespace, list_writer)
+    }
+
+    fn remove_xattr(&self, name: XattrName) -> Result<()> {
+        self.copy_up_locked()?.remove_xattr(name)
+    }
+}
+
+impl Debug for OverlayInode {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("OverlayInode")
+            .field("ino", &self.ino)
+            .field("type_", &self.type_)
+            .field("is_upper", &self.upper.read().is_some())
+            .field("num_lowers", &self.lowers.len())
+            .finish()
+    }
+}
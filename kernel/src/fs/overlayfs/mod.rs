@@ -0,0 +1,21 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The overlay file system, which merges the directory trees of several layers.
+//!
+//! The layers are directories of other file systems. The lower layers are never
+//! modified, and all the changes go to the upper layer, if any:
+//! - A file of the lower layers is copied up to the upper layer before it is written,
+//!   staged in the work directory and then moved into place;
+//! - A removed entry of the lower layers is hidden by a whiteout in the upper layer,
+//!   i.e., a character device with the device number of zero;
+//! - A directory that must not be merged with the lower layers, e.g., the one which is
+//!   created in the place of a whiteout, is marked by the `trusted.overlay.opaque` xattr.
+//!
+//! Without an upper layer, the overlay file system is read-only.
+
+mod fs;
+mod inode;
+mod whiteout;
+
+pub use fs::{OverlayFs, OverlayMountOptions};
+pub use inode::OverlayInode;
@@ -0,0 +1,122 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Whiteouts and opaque directories, which are recorded in the upper layer.
+
+use crate::{
+    events::IoEvents,
+    fs::{
+        device::{Device, DeviceId, DeviceType},
+        inode_handle::FileIo,
+        utils::{Inode, InodeMode, InodeType, MknodType, XattrName, XattrSetFlags},
+    },
+    prelude::*,
+    process::signal::{PollHandle, Pollable},
+};
+
+const OPAQUE_XATTR: &str = "trusted.overlay.opaque";
+const ORIGIN_XATTR: &str = "trusted.overlay.origin";
+
+/// The device of whiteouts, whose device number is zero.
+///
+/// It is only used to create whiteouts through [`Inode::mknod`], and a whiteout is
+/// never opened since it is hidden by the overlay file system.
+struct Whiteout;
+
+impl Device for Whiteout {
+    fn type_(&self) -> DeviceType {
+        DeviceType::CharDevice
+    }
+
+    fn id(&self) -> DeviceId {
+        DeviceId::new(0, 0)
+    }
+}
+
+impl Pollable for Whiteout {
+    fn poll(&self, _mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        IoEvents::empty()
+    }
+}
+
+impl FileIo for Whiteout {
+    fn read(&self, _writer: &mut VmWriter) -> Result<usize> {
+        return_errno_with_message!(Errno::ENXIO, "a whiteout cannot be read");
+    }
+
+    fn write(&self, _reader: &mut VmReader) -> Result<usize> {
+        return_errno_with_message!(Errno::ENXIO, "a whiteout cannot be written");
+    }
+}
+
+/// Returns whether the inode of the upper layer is a whiteout.
+pub(super) fn is_whiteout(inode: &Arc<dyn Inode>) -> bool {
+    if inode.type_() != InodeType::CharDevice {
+        return false;
+    }
+    inode.metadata().rdev == 0
+}
+
+/// Creates a whiteout named `name` in the directory of the upper layer.
+pub(super) fn create_whiteout(dir: &Arc<dyn Inode>, name: &str) -> Result<()> {
+    let device: Arc<dyn Device> = Arc::new(Whiteout);
+    dir.mknod(
+        name,
+        InodeMode::from_bits_truncate(0),
+        MknodType::from(device),
+    )?;
+    Ok(())
+}
+
+/// Removes the whiteout named `name` from the directory of the upper layer.
+///
+/// Returns whether there was a whiteout.
+pub(super) fn remove_whiteout(dir: &Arc<dyn Inode>, name: &str) -> Result<bool> {
+    match dir.lookup(name) {
+        Ok(inode) if is_whiteout(&inode) => {
+            dir.unlink(name)?;
+            Ok(true)
+        }
+        Ok(_) => Ok(false),
+        Err(err) if err.error() == Errno::ENOENT => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+/// Returns whether the directory stops the merging with the lower layers.
+pub(super) fn is_opaque(dir: &Arc<dyn Inode>) -> bool {
+    let mut value = [0u8; 1];
+    let mut writer = VmWriter::from(value.as_mut_slice()).to_fallible();
+    let name = XattrName::try_from_full_name(OPAQUE_XATTR).unwrap();
+    matches!(dir.get_xattr(name, &mut writer), Ok(1)) && value[0] == b'y'
+}
+
+pub(super) fn set_opaque(dir: &Arc<dyn Inode>) -> Result<()> {
+    let mut reader = VmReader::from(b"y".as_slice()).to_fallible();
+    let name = XattrName::try_from_full_name(OPAQUE_XATTR).unwrap();
+    dir.set_xattr(name, &mut reader, XattrSetFlags::CREATE_OR_REPLACE)
+}
+
+/// Returns the inode number of the lower file, from which the file of the
+/// upper layer was copied up.
+pub(super) fn origin_ino(inode: &Arc<dyn Inode>) -> Option<u64> {
+    let mut value = [0u8; 8];
+    let mut writer = VmWriter::from(value.as_mut_slice()).to_fallible();
+    let name = XattrName::try_from_full_name(ORIGIN_XATTR).unwrap();
+    match inode.get_xattr(name, &mut writer) {
+        Ok(8) => Some(u64::from_le_bytes(value)),
+        _ => None,
+    }
+}
+
+/// Records the inode number of the lower file, which keeps the inode number
+/// reported by the overlay file system unchanged after a copy-up.
+///
+/// The record is best-effort since the upper layer may not support xattrs.
+pub(super) fn set_origin_ino(inode: &Arc<dyn Inode>, ino: u64) {
+    let bytes = ino.to_le_bytes();
+    let mut reader = VmReader::from(bytes.as_slice()).to_fallible();
+    let name = XattrName::try_from_full_name(ORIGIN_XATTR).unwrap();
+    if let Err(err) = inode.set_xattr(name, &mut reader, XattrSetFlags::CREATE_OR_REPLACE) {
+        debug!("failed to record the origin of a copy-up: {:?}", err);
+    }
+}
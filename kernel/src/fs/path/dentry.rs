@@ -15,7 +15,7 @@ use ostd::sync::RwMutexWriteGuard;
 use super::{is_dot, is_dot_or_dotdot, is_dotdot};
 use crate::{
     fs::{
-        path::mount::MountNode,
+        path::mount::{MountNode, PerMountFlags},
         utils::{
             FileSystem, Inode, InodeMode, InodeType, Metadata, MknodType, Permission, XattrName,
             XattrNamespace, XattrSetFlags, NAME_MAX,
@@ -23,6 +23,7 @@ use crate::{
     },
     prelude::*,
     process::{Gid, Uid},
+    security::hooks,
 };
 
 /// A `Dentry` is used to represent a location in the mount tree.
@@ -334,6 +335,67 @@ impl Dentry_ {
         }
         Ok(())
     }
+
+    /// Exchanges the dentry of `old_name` in this directory and that of `new_name`
+    /// in `new_dir` atomically.
+    pub fn exchange(&self, old_name: &str, new_dir: &Arc<Self>, new_name: &str) -> Result<()> {
+        if is_dot_or_dotdot(old_name) || is_dot_or_dotdot(new_name) {
+            return_errno_with_message!(Errno::EBUSY, "old_name or new_name is . or ..");
+        }
+        if self.type_() != InodeType::Dir || new_dir.type_() != InodeType::Dir {
+            return_errno!(Errno::ENOTDIR);
+        }
+
+        if Arc::ptr_eq(&self.this(), new_dir) {
+            if old_name == new_name {
+                return Ok(());
+            }
+
+            let mut children = self.children.write();
+            let old_dentry = children.check_mountpoint_then_find(old_name)?;
+            let new_dentry = children.check_mountpoint_then_find(new_name)?;
+
+            self.inode.exchange(old_name, &self.inode, new_name)?;
+
+            children.delete(old_name);
+            children.delete(new_name);
+            if let Some(dentry) = old_dentry {
+                dentry.set_name_and_parent(new_name, self.this());
+                if dentry.is_dentry_cacheable() {
+                    children.insert(String::from(new_name), dentry);
+                }
+            }
+            if let Some(dentry) = new_dentry {
+                dentry.set_name_and_parent(old_name, self.this());
+                if dentry.is_dentry_cacheable() {
+                    children.insert(String::from(old_name), dentry);
+                }
+            }
+        } else {
+            let (mut self_children, mut new_dir_children) =
+                write_lock_children_on_two_dentries(self, new_dir);
+            let old_dentry = self_children.check_mountpoint_then_find(old_name)?;
+            let new_dentry = new_dir_children.check_mountpoint_then_find(new_name)?;
+
+            self.inode.exchange(old_name, &new_dir.inode, new_name)?;
+
+            self_children.delete(old_name);
+            new_dir_children.delete(new_name);
+            if let Some(dentry) = old_dentry {
+                dentry.set_name_and_parent(new_name, new_dir.this());
+                if dentry.is_dentry_cacheable() {
+                    new_dir_children.insert(String::from(new_name), dentry);
+                }
+            }
+            if let Some(dentry) = new_dentry {
+                dentry.set_name_and_parent(old_name, self.this());
+                if dentry.is_dentry_cacheable() {
+                    self_children.insert(String::from(old_name), dentry);
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 #[inherit_methods(from = "self.inode")]
@@ -525,6 +587,7 @@ impl Dentry {
 
     /// Creates a new `Dentry` to represent the child directory of a file system.
     pub fn new_fs_child(&self, name: &str, type_: InodeType, mode: InodeMode) -> Result<Self> {
+        self.check_mount_writable()?;
         if self
             .inode()
             .check_permission(Permission::MAY_WRITE)
@@ -532,6 +595,7 @@ impl Dentry {
         {
             return_errno!(Errno::EACCES);
         }
+        hooks::inode_create(self, type_)?;
         let new_child_dentry = self.inner.create(name, type_, mode)?;
         Ok(Self::new(self.mount_node.clone(), new_child_dentry))
     }
@@ -620,7 +684,7 @@ impl Dentry {
     ///
     /// If it is the root of a mount, it will go up to the mountpoint
     /// to get the parent of the mountpoint recursively.
-    fn effective_parent(&self) -> Option<Self> {
+    pub fn effective_parent(&self) -> Option<Self> {
         if !self.inner.is_root_of_mount() {
             return Some(Self::new(
                 self.mount_node.clone(),
@@ -705,6 +769,8 @@ impl Dentry {
 
     /// Creates a `Dentry` by making an inode of the `type_` with the `mode`.
     pub fn mknod(&self, name: &str, mode: InodeMode, type_: MknodType) -> Result<Self> {
+        self.check_mount_writable()?;
+        hooks::inode_create(self, type_.inode_type())?;
         let inner = self.inner.mknod(name, mode, type_)?;
         Ok(Self::new(self.mount_node.clone(), inner))
     }
@@ -714,16 +780,22 @@ impl Dentry {
         if !Arc::ptr_eq(&old.mount_node, &self.mount_node) {
             return_errno_with_message!(Errno::EXDEV, "cannot cross mount");
         }
+        self.check_mount_writable()?;
+        hooks::inode_link(old, self)?;
         self.inner.link(&old.inner, name)
     }
 
     /// Deletes a `Dentry`.
     pub fn unlink(&self, name: &str) -> Result<()> {
+        self.check_mount_writable()?;
+        hooks::inode_unlink(self, name)?;
         self.inner.unlink(name)
     }
 
     /// Deletes a directory `Dentry`.
     pub fn rmdir(&self, name: &str) -> Result<()> {
+        self.check_mount_writable()?;
+        hooks::inode_rmdir(self, name)?;
         self.inner.rmdir(name)
     }
 
@@ -732,9 +804,22 @@ impl Dentry {
         if !Arc::ptr_eq(&self.mount_node, &new_dir.mount_node) {
             return_errno_with_message!(Errno::EXDEV, "cannot cross mount");
         }
+        self.check_mount_writable()?;
+        hooks::inode_rename(self, old_name, new_dir, new_name, false)?;
         self.inner.rename(old_name, &new_dir.inner, new_name)
     }
 
+    /// Exchanges the file of `old_name` in this directory and that of `new_name`
+    /// in `new_dir` atomically.
+    pub fn exchange(&self, old_name: &str, new_dir: &Self, new_name: &str) -> Result<()> {
+        if !Arc::ptr_eq(&self.mount_node, &new_dir.mount_node) {
+            return_errno_with_message!(Errno::EXDEV, "cannot cross mount");
+        }
+        self.check_mount_writable()?;
+        hooks::inode_rename(self, old_name, new_dir, new_name, true)?;
+        self.inner.exchange(old_name, &new_dir.inner, new_name)
+    }
+
     /// Binds mount the `Dentry` to the destination `Dentry`.
     ///
     /// If `recursive` is true, it will bind mount the whole mount tree
@@ -756,6 +841,49 @@ impl Dentry {
     pub fn mount_node(&self) -> &Arc<MountNode> {
         &self.mount_node
     }
+
+    /// Checks whether the file can be modified via the mount.
+    pub fn check_mount_writable(&self) -> Result<()> {
+        if self.mount_node.flags().contains(PerMountFlags::RDONLY) {
+            return_errno_with_message!(Errno::EROFS, "the mount is read-only");
+        }
+        Ok(())
+    }
+
+    pub fn set_mode(&self, mode: InodeMode) -> Result<()> {
+        self.check_mount_writable()?;
+        self.inner.set_mode(mode)
+    }
+
+    pub fn resize(&self, size: usize) -> Result<()> {
+        self.check_mount_writable()?;
+        self.inner.resize(size)
+    }
+
+    pub fn set_owner(&self, uid: Uid) -> Result<()> {
+        self.check_mount_writable()?;
+        self.inner.set_owner(uid)
+    }
+
+    pub fn set_group(&self, gid: Gid) -> Result<()> {
+        self.check_mount_writable()?;
+        self.inner.set_group(gid)
+    }
+
+    pub fn set_xattr(
+        &self,
+        name: XattrName,
+        value_reader: &mut VmReader,
+        flags: XattrSetFlags,
+    ) -> Result<()> {
+        self.check_mount_writable()?;
+        self.inner.set_xattr(name, value_reader, flags)
+    }
+
+    pub fn remove_xattr(&self, name: XattrName) -> Result<()> {
+        self.check_mount_writable()?;
+        self.inner.remove_xattr(name)
+    }
 }
 
 #[inherit_methods(from = "self.inner")]
@@ -766,13 +894,9 @@ impl Dentry {
     pub fn metadata(&self) -> Metadata;
     pub fn type_(&self) -> InodeType;
     pub fn mode(&self) -> Result<InodeMode>;
-    pub fn set_mode(&self, mode: InodeMode) -> Result<()>;
     pub fn size(&self) -> usize;
-    pub fn resize(&self, size: usize) -> Result<()>;
     pub fn owner(&self) -> Result<Uid>;
-    pub fn set_owner(&self, uid: Uid) -> Result<()>;
     pub fn group(&self) -> Result<Gid>;
-    pub fn set_group(&self, gid: Gid) -> Result<()>;
     pub fn atime(&self) -> Duration;
     pub fn set_atime(&self, time: Duration);
     pub fn mtime(&self) -> Duration;
@@ -783,17 +907,10 @@ impl Dentry {
     pub fn inode(&self) -> &Arc<dyn Inode>;
     pub fn is_root_of_mount(&self) -> bool;
     pub fn is_mountpoint(&self) -> bool;
-    pub fn set_xattr(
-        &self,
-        name: XattrName,
-        value_reader: &mut VmReader,
-        flags: XattrSetFlags,
-    ) -> Result<()>;
     pub fn get_xattr(&self, name: XattrName, value_writer: &mut VmWriter) -> Result<usize>;
     pub fn list_xattr(
         &self,
         namespace: XattrNamespace,
         list_writer: &mut VmWriter,
     ) -> Result<usize>;
-    pub fn remove_xattr(&self, name: XattrName) -> Result<()>;
 }
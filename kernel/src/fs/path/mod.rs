@@ -3,7 +3,7 @@
 //! Form file paths within and across FSes with dentries and mount points.
 
 pub use dentry::{Dentry, DentryKey};
-pub use mount::MountNode;
+pub use mount::{MountNode, PerMountFlags};
 
 mod dentry;
 mod mount;
@@ -23,6 +23,8 @@ pub struct MountNode {
     parent: RwLock<Option<Weak<MountNode>>>,
     /// Child mount nodes which are mounted on one dentry of self.
     children: RwLock<HashMap<DentryKey, Arc<Self>>>,
+    /// The flags that apply to the files accessed via this mount.
+    flags: RwLock<PerMountFlags>,
     /// Reference to self.
     this: Weak<Self>,
 }
@@ -53,6 +55,7 @@ impl MountNode {
             mountpoint_dentry: RwLock::new(None),
             parent: RwLock::new(parent_mount),
             children: RwLock::new(HashMap::new()),
+            flags: RwLock::new(PerMountFlags::empty()),
             fs,
             this: weak_self.clone(),
         })
@@ -101,7 +104,7 @@ impl MountNode {
 
     /// Clones a mount node with the an root `Dentry_`.
     ///
-    /// The new mount node will have the same fs as the original one and
+    /// The new mount node will have the same fs and flags as the original one and
     /// have no parent and children. We should set the parent and children manually.
     fn clone_mount_node(&self, root_dentry: &Arc<Dentry_>) -> Arc<Self> {
         Arc::new_cyclic(|weak_self| Self {
@@ -109,6 +112,7 @@ impl MountNode {
             mountpoint_dentry: RwLock::new(None),
             parent: RwLock::new(None),
             children: RwLock::new(HashMap::new()),
+            flags: RwLock::new(self.flags()),
             fs: self.fs.clone(),
             this: weak_self.clone(),
         })
@@ -256,6 +260,16 @@ impl MountNode {
     pub fn fs(&self) -> &Arc<dyn FileSystem> {
         &self.fs
     }
+
+    /// Gets the flags of the mount.
+    pub fn flags(&self) -> PerMountFlags {
+        *self.flags.read()
+    }
+
+    /// Sets the flags of the mount, e.g., when it is remounted.
+    pub fn set_flags(&self, flags: PerMountFlags) {
+        *self.flags.write() = flags;
+    }
 }
 
 impl Debug for MountNode {
@@ -264,6 +278,30 @@ impl Debug for MountNode {
             .field("root", &self.root_dentry)
             .field("mountpoint", &self.mountpoint_dentry)
             .field("fs", &self.fs)
+            .field("flags", &self.flags())
             .finish()
     }
 }
+
+bitflags! {
+    /// The flags of a mount, whose values are the same as `MOUNT_ATTR_*` in Linux.
+    ///
+    /// Unlike the flags of a file system, they are specific to a mount. So a file system
+    /// can be accessed read-only via one mount and read-write via another.
+    pub struct PerMountFlags: u32 {
+        /// Files cannot be modified.
+        const RDONLY = 1 << 0;
+        /// The set-user-ID and set-group-ID bits are ignored by `execve`.
+        const NOSUID = 1 << 1;
+        /// Device files cannot be opened.
+        const NODEV = 1 << 2;
+        /// Files cannot be executed.
+        const NOEXEC = 1 << 3;
+        /// Access times are not updated.
+        const NOATIME = 1 << 4;
+        /// Access times of directories are not updated.
+        const NODIRATIME = 1 << 7;
+        /// Symlinks are not followed.
+        const NOSYMFOLLOW = 1 << 21;
+    }
+}
@@ -0,0 +1,210 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use super::{ring::PipeRing, PIPE_BUF};
+use crate::{
+    events::IoEvents,
+    prelude::*,
+    process::signal::{PollHandle, Pollee},
+    util::{MultiRead, MultiWrite},
+};
+
+/// The state shared by the two ends of a pipe.
+pub(super) struct Pipe {
+    ring: Mutex<PipeRing>,
+    /// The pollee of the read end.
+    read_pollee: Pollee,
+    /// The pollee of the write end.
+    write_pollee: Pollee,
+    is_shutdown: AtomicBool,
+}
+
+impl Pipe {
+    pub(super) fn new(max_bufs: usize) -> Arc<Self> {
+        Arc::new(Self {
+            ring: Mutex::new(PipeRing::new(max_bufs)),
+            read_pollee: Pollee::new(),
+            write_pollee: Pollee::new(),
+            is_shutdown: AtomicBool::new(false),
+        })
+    }
+
+    /// Tries to read data from the pipe.
+    ///
+    /// - Returns `Ok(_)` with the number of bytes read if successful.
+    /// - Returns `Ok(0)` if the pipe is shut down and there is no data left.
+    /// - Returns `Err(EAGAIN)` if the pipe is empty.
+    pub(super) fn try_read(&self, writer: &mut dyn MultiWrite) -> Result<usize> {
+        if writer.is_empty() {
+            return Ok(0);
+        }
+
+        let max_len = writer.sum_lens();
+        self.try_read_with(max_len, |mut reader| writer.write(&mut reader))
+    }
+
+    /// Tries to read at most `max_len` bytes from the pipe by `read_fn`.
+    ///
+    /// See [`PipeRing::read_with`] for the requirements of `read_fn`.
+    pub(super) fn try_read_with<F>(&self, max_len: usize, read_fn: F) -> Result<usize>
+    where
+        F: FnMut(VmReader<'_, Infallible>) -> Result<usize>,
+    {
+        if max_len == 0 {
+            return Ok(0);
+        }
+
+        // This must be recorded before the actual operation to avoid race conditions.
+        let is_shutdown = self.is_shutdown();
+
+        let read_len = {
+            let mut ring = self.ring.lock();
+            if ring.is_empty() {
+                if is_shutdown {
+                    return Ok(0);
+                }
+                return_errno_with_message!(Errno::EAGAIN, "the pipe is empty");
+            }
+            ring.read_with(max_len, read_fn)?
+        };
+        self.write_pollee.notify(IoEvents::OUT);
+        self.read_pollee.invalidate();
+
+        Ok(read_len)
+    }
+
+    /// Tries to write data to the pipe.
+    ///
+    /// A write of at most [`PIPE_BUF`] bytes is atomic.
+    ///
+    /// - Returns `Ok(_)` with the number of bytes written if successful.
+    /// - Returns `Err(EPIPE)` if the pipe is shut down.
+    /// - Returns `Err(EAGAIN)` if the pipe is full.
+    pub(super) fn try_write(&self, reader: &mut dyn MultiRead, is_packet: bool) -> Result<usize> {
+        if reader.is_empty() {
+            // Even after shutdown, writing an empty buffer is still fine.
+            return Ok(0);
+        }
+
+        let max_len = reader.sum_lens();
+        let is_atomic = max_len <= PIPE_BUF;
+        let written_len = self.write_to_ring(max_len, is_packet, is_atomic, |mut writer| {
+            reader.read(&mut writer)
+        })?;
+
+        if written_len > 0 {
+            Ok(written_len)
+        } else {
+            return_errno_with_message!(Errno::EAGAIN, "the pipe is full");
+        }
+    }
+
+    /// Tries to write at most `max_len` bytes to the pipe by `write_fn`.
+    ///
+    /// See [`PipeRing::write_with`] for the requirements of `write_fn`. Unlike
+    /// [`Self::try_write`], the write is never atomic.
+    ///
+    /// - Returns `Ok(_)` with the number of bytes written if successful, which is zero
+    ///   if `write_fn` has nothing to write.
+    /// - Returns `Err(EPIPE)` if the pipe is shut down.
+    /// - Returns `Err(EAGAIN)` if the pipe is full.
+    pub(super) fn try_write_with<F>(
+        &self,
+        max_len: usize,
+        is_packet: bool,
+        write_fn: F,
+    ) -> Result<usize>
+    where
+        F: FnMut(VmWriter<'_, Infallible>) -> Result<usize>,
+    {
+        if max_len == 0 {
+            return Ok(0);
+        }
+
+        self.write_to_ring(max_len, is_packet, false, write_fn)
+    }
+
+    fn write_to_ring<F>(
+        &self,
+        max_len: usize,
+        is_packet: bool,
+        is_atomic: bool,
+        write_fn: F,
+    ) -> Result<usize>
+    where
+        F: FnMut(VmWriter<'_, Infallible>) -> Result<usize>,
+    {
+        if self.is_shutdown() {
+            return_errno_with_message!(Errno::EPIPE, "the pipe is shut down");
+        }
+
+        let written_len = {
+            let mut ring = self.ring.lock();
+            let free_len = ring.free_len(is_packet);
+            if free_len == 0 || (is_atomic && free_len < max_len) {
+                return_errno_with_message!(Errno::EAGAIN, "the pipe is full");
+            }
+            ring.write_with(max_len, is_packet, write_fn)?
+        };
+        if written_len > 0 {
+            self.read_pollee.notify(IoEvents::IN);
+        }
+
+        Ok(written_len)
+    }
+
+    pub(super) fn capacity(&self) -> usize {
+        self.ring.lock().capacity()
+    }
+
+    /// Changes the capacity, which must be a multiple of `PAGE_SIZE`.
+    pub(super) fn set_capacity(&self, capacity: usize) -> Result<()> {
+        debug_assert!(capacity % PAGE_SIZE == 0);
+        self.ring.lock().set_max_bufs(capacity / PAGE_SIZE)?;
+        self.write_pollee.notify(IoEvents::OUT);
+        Ok(())
+    }
+
+    pub(super) fn poll_read(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
+        self.read_pollee.poll_with(mask, poller, || {
+            let mut events = IoEvents::empty();
+            if self.is_shutdown() {
+                events |= IoEvents::HUP;
+            }
+            if !self.ring.lock().is_empty() {
+                events |= IoEvents::IN;
+            }
+            events
+        })
+    }
+
+    pub(super) fn poll_write(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
+        self.write_pollee.poll_with(mask, poller, || {
+            if self.is_shutdown() {
+                IoEvents::ERR | IoEvents::OUT
+            } else if self.ring.lock().has_free_buf() {
+                IoEvents::OUT
+            } else {
+                IoEvents::empty()
+            }
+        })
+    }
+
+    pub(super) fn is_shutdown(&self) -> bool {
+        self.is_shutdown.load(Ordering::Relaxed)
+    }
+
+    pub(super) fn shutdown(&self) {
+        if self.is_shutdown.swap(true, Ordering::Relaxed) {
+            return;
+        }
+
+        // The POLLHUP event indicates that the write end is shut down.
+        self.read_pollee.notify(IoEvents::HUP);
+
+        // The POLLERR event indicates that the read end is shut down (so any subsequent writes
+        // will fail with an `EPIPE` error).
+        self.write_pollee.notify(IoEvents::ERR | IoEvents::OUT);
+    }
+}
@@ -0,0 +1,494 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+use self::common::Pipe;
+use super::{
+    file_handle::FileLike,
+    utils::{AccessMode, InodeMode, InodeType, Metadata, StatusFlags},
+};
+use crate::{
+    events::IoEvents,
+    prelude::*,
+    process::{
+        signal::{PollHandle, Pollable},
+        Gid, Uid,
+    },
+    time::clocks::RealTimeCoarseClock,
+    util::{MultiRead, MultiWrite},
+};
+
+mod common;
+mod ring;
+
+/// The maximum number of bytes that can be written to a pipe atomically.
+///
+/// For more details, see the description of `PIPE_BUF` in
+/// <https://man7.org/linux/man-pages/man7/pipe.7.html>.
+const PIPE_BUF: usize = PAGE_SIZE;
+
+const DEFAULT_PIPE_BUF_SIZE: usize = 65536;
+
+/// The maximum capacity of a pipe that an unprivileged process can set.
+///
+/// The default value is the same as that of `/proc/sys/fs/pipe-max-size` on Linux.
+static PIPE_MAX_SIZE: AtomicUsize = AtomicUsize::new(1024 * 1024);
+
+/// Returns the maximum capacity of a pipe that an unprivileged process can set.
+pub fn pipe_max_size() -> usize {
+    PIPE_MAX_SIZE.load(Ordering::Relaxed)
+}
+
+/// Sets the maximum capacity of a pipe that an unprivileged process can set, which is rounded
+/// up in the same way as the capacity of a pipe.
+pub fn set_pipe_max_size(size: usize) -> Result<()> {
+    PIPE_MAX_SIZE.store(round_pipe_size(size)?, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Rounds the capacity of a pipe up to a power-of-two number of pages, as Linux does.
+pub fn round_pipe_size(size: usize) -> Result<usize> {
+    if size > 1 << 31 {
+        return_errno_with_message!(Errno::EINVAL, "the pipe size is too large");
+    }
+    Ok(size.div_ceil(PAGE_SIZE).max(1).next_power_of_two() * PAGE_SIZE)
+}
+
+pub fn new_pair() -> Result<(Arc<PipeReader>, Arc<PipeWriter>)> {
+    new_pair_with_capacity(DEFAULT_PIPE_BUF_SIZE)
+}
+
+pub fn new_pair_with_capacity(capacity: usize) -> Result<(Arc<PipeReader>, Arc<PipeWriter>)> {
+    let pipe = Pipe::new(capacity.div_ceil(PAGE_SIZE).max(1));
+
+    Ok((PipeReader::new(pipe.clone()), PipeWriter::new(pipe)))
+}
+
+pub struct PipeReader {
+    pipe: Arc<Pipe>,
+    status_flags: AtomicU32,
+}
+
+impl PipeReader {
+    fn new(pipe: Arc<Pipe>) -> Arc<Self> {
+        Arc::new(Self {
+            pipe,
+            status_flags: AtomicU32::new(0),
+        })
+    }
+
+    /// Returns whether the writer is the other end of this pipe.
+    pub fn is_peer_of(&self, writer: &PipeWriter) -> bool {
+        Arc::ptr_eq(&self.pipe, &writer.pipe)
+    }
+
+    /// Returns the capacity of the pipe in bytes.
+    pub fn capacity(&self) -> usize {
+        self.pipe.capacity()
+    }
+
+    /// Changes the capacity of the pipe, which must be a multiple of `PAGE_SIZE`.
+    ///
+    /// It fails with `EBUSY` if the data in the pipe do not fit in the new capacity.
+    pub fn set_capacity(&self, capacity: usize) -> Result<()> {
+        self.pipe.set_capacity(capacity)
+    }
+
+    /// Reads data from the pipe to `writer`, which may consist of multiple buffers.
+    pub fn read_vectored(
+        &self,
+        writer: &mut dyn MultiWrite,
+        is_nonblocking: bool,
+    ) -> Result<usize> {
+        if is_nonblocking {
+            self.pipe.try_read(writer)
+        } else {
+            self.wait_events(IoEvents::IN, None, || self.pipe.try_read(writer))
+        }
+    }
+
+    /// Moves at most `max_len` bytes out of the pipe by `read_fn`.
+    ///
+    /// The `read_fn` is given the `VmReader` of a pipe buffer and returns the
+    /// number of bytes consumed, so `splice()` does not need a temporary buffer.
+    pub fn splice_to<F>(
+        &self,
+        max_len: usize,
+        is_nonblocking: bool,
+        mut read_fn: F,
+    ) -> Result<usize>
+    where
+        F: FnMut(&mut VmReader) -> Result<usize>,
+    {
+        let mut try_splice = || {
+            self.pipe
+                .try_read_with(max_len, |reader| read_fn(&mut reader.to_fallible()))
+        };
+
+        if is_nonblocking {
+            try_splice()
+        } else {
+            self.wait_events(IoEvents::IN, None, try_splice)
+        }
+    }
+}
+
+impl Pollable for PipeReader {
+    fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
+        self.pipe.poll_read(mask, poller)
+    }
+}
+
+impl Drop for PipeReader {
+    fn drop(&mut self) {
+        self.pipe.shutdown();
+    }
+}
+
+impl FileLike for PipeReader {
+    fn read(&self, writer: &mut VmWriter) -> Result<usize> {
+        let is_nonblocking = self.status_flags().contains(StatusFlags::O_NONBLOCK);
+        self.read_vectored(writer, is_nonblocking)
+    }
+
+    fn status_flags(&self) -> StatusFlags {
+        StatusFlags::from_bits_truncate(self.status_flags.load(Ordering::Relaxed))
+    }
+
+    fn set_status_flags(&self, new_flags: StatusFlags) -> Result<()> {
+        // TODO: Setting most of the flags will succeed on Linux, but their effects need to be
+        // validated.
+        self.status_flags.store(new_flags.bits(), Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn access_mode(&self) -> AccessMode {
+        AccessMode::O_RDONLY
+    }
+
+    fn metadata(&self) -> Metadata {
+        // This is a dummy implementation.
+        // TODO: Add "PipeFS" and link `PipeReader` to it.
+        let now = RealTimeCoarseClock::get().read_time();
+        Metadata {
+            dev: 0,
+            ino: 0,
+            size: 0,
+            blk_size: 0,
+            blocks: 0,
+            atime: now,
+            mtime: now,
+            ctime: now,
+            btime: None,
+            type_: InodeType::NamedPipe,
+            mode: InodeMode::from_bits_truncate(0o400),
+            nlinks: 1,
+            uid: Uid::new_root(),
+            gid: Gid::new_root(),
+            rdev: 0,
+        }
+    }
+}
+
+pub struct PipeWriter {
+    pipe: Arc<Pipe>,
+    status_flags: AtomicU32,
+}
+
+impl PipeWriter {
+    fn new(pipe: Arc<Pipe>) -> Arc<Self> {
+        Arc::new(Self {
+            pipe,
+            status_flags: AtomicU32::new(0),
+        })
+    }
+
+    /// Returns the capacity of the pipe in bytes.
+    pub fn capacity(&self) -> usize {
+        self.pipe.capacity()
+    }
+
+    /// Changes the capacity of the pipe, which must be a multiple of `PAGE_SIZE`.
+    ///
+    /// It fails with `EBUSY` if the data in the pipe do not fit in the new capacity.
+    pub fn set_capacity(&self, capacity: usize) -> Result<()> {
+        self.pipe.set_capacity(capacity)
+    }
+
+    /// Writes data from `reader`, which may consist of multiple buffers, to the pipe.
+    pub fn write_vectored(
+        &self,
+        reader: &mut dyn MultiRead,
+        is_nonblocking: bool,
+    ) -> Result<usize> {
+        let is_packet = self.is_packet_mode();
+
+        if is_nonblocking {
+            self.pipe.try_write(reader, is_packet)
+        } else {
+            self.wait_events(IoEvents::OUT, None, || {
+                self.pipe.try_write(reader, is_packet)
+            })
+        }
+    }
+
+    /// Moves at most `max_len` bytes into the pipe by `write_fn`.
+    ///
+    /// The `write_fn` is given the `VmWriter` of a pipe buffer and returns the
+    /// number of bytes written, so `splice()` does not need a temporary buffer.
+    pub fn splice_from<F>(
+        &self,
+        max_len: usize,
+        is_nonblocking: bool,
+        mut write_fn: F,
+    ) -> Result<usize>
+    where
+        F: FnMut(&mut VmWriter) -> Result<usize>,
+    {
+        let is_packet = self.is_packet_mode();
+        let mut try_splice = || {
+            self.pipe.try_write_with(max_len, is_packet, |writer| {
+                write_fn(&mut writer.to_fallible())
+            })
+        };
+
+        if is_nonblocking {
+            try_splice()
+        } else {
+            self.wait_events(IoEvents::OUT, None, try_splice)
+        }
+    }
+
+    /// Returns whether the data are written as packets.
+    ///
+    /// "O_DIRECT (since Linux 3.4) Create a pipe that performs I/O in "packet" mode. Each
+    /// write(2) to the pipe is dealt with as a separate packet, and read(2)s from the pipe
+    /// will read one packet at a time."
+    ///
+    /// See <https://man7.org/linux/man-pages/man2/pipe.2.html>.
+    fn is_packet_mode(&self) -> bool {
+        self.status_flags().contains(StatusFlags::O_DIRECT)
+    }
+}
+
+impl Pollable for PipeWriter {
+    fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
+        self.pipe.poll_write(mask, poller)
+    }
+}
+
+impl Drop for PipeWriter {
+    fn drop(&mut self) {
+        self.pipe.shutdown();
+    }
+}
+
+impl FileLike for PipeWriter {
+    fn write(&self, reader: &mut VmReader) -> Result<usize> {
+        let is_nonblocking = self.status_flags().contains(StatusFlags::O_NONBLOCK);
+        self.write_vectored(reader, is_nonblocking)
+    }
+
+    fn status_flags(&self) -> StatusFlags {
+        StatusFlags::from_bits_truncate(self.status_flags.load(Ordering::Relaxed))
+    }
+
+    fn set_status_flags(&self, new_flags: StatusFlags) -> Result<()> {
+        // TODO: Setting most of the flags will succeed on Linux, but their effects need to be
+        // validated.
+        self.status_flags.store(new_flags.bits(), Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn access_mode(&self) -> AccessMode {
+        AccessMode::O_WRONLY
+    }
+
+    fn metadata(&self) -> Metadata {
+        // This is a dummy implementation.
+        // TODO: Add "PipeFS" and link `PipeWriter` to it.
+        let now = RealTimeCoarseClock::get().read_time();
+        Metadata {
+            dev: 0,
+            ino: 0,
+            size: 0,
+            blk_size: 0,
+            blocks: 0,
+            atime: now,
+            mtime: now,
+            ctime: now,
+            btime: None,
+            type_: InodeType::NamedPipe,
+            mode: InodeMode::from_bits_truncate(0o200),
+            nlinks: 1,
+            uid: Uid::new_root(),
+            gid: Gid::new_root(),
+            rdev: 0,
+        }
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use alloc::sync::Arc;
+    use core::sync::atomic::{self, AtomicBool};
+
+    use ostd::prelude::*;
+
+    use super::*;
+    use crate::thread::{kernel_thread::ThreadOptions, Thread};
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    enum Ordering {
+        WriteThenRead,
+        ReadThenWrite,
+    }
+
+    fn test_blocking<W, R>(write: W, read: R, ordering: Ordering)
+    where
+        W: FnOnce(Arc<PipeWriter>) + Send + 'static,
+        R: FnOnce(Arc<PipeReader>) + Send + 'static,
+    {
+        let (reader, writer) = new_pair_with_capacity(PAGE_SIZE).unwrap();
+
+        let signal_writer = Arc::new(AtomicBool::new(false));
+        let signal_reader = signal_writer.clone();
+
+        let writer = ThreadOptions::new(move || {
+            if ordering == Ordering::ReadThenWrite {
+                while !signal_writer.load(atomic::Ordering::Relaxed) {
+                    Thread::yield_now();
+                }
+            } else {
+                signal_writer.store(true, atomic::Ordering::Relaxed);
+            }
+
+            write(writer);
+        })
+        .spawn();
+
+        let reader = ThreadOptions::new(move || {
+            if ordering == Ordering::WriteThenRead {
+                while !signal_reader.load(atomic::Ordering::Relaxed) {
+                    Thread::yield_now();
+                }
+            } else {
+                signal_reader.store(true, atomic::Ordering::Relaxed);
+            }
+
+            read(reader);
+        })
+        .spawn();
+
+        writer.join();
+        reader.join();
+    }
+
+    #[ktest]
+    fn test_read_empty() {
+        test_blocking(
+            |writer| {
+                assert_eq!(writer.write(&mut reader_from(&[1])).unwrap(), 1);
+            },
+            |reader| {
+                let mut buf = [0; 1];
+                assert_eq!(reader.read(&mut writer_from(&mut buf)).unwrap(), 1);
+                assert_eq!(&buf, &[1]);
+            },
+            Ordering::ReadThenWrite,
+        );
+    }
+
+    #[ktest]
+    fn test_write_full() {
+        test_blocking(
+            |writer| {
+                let buf = vec![1; PAGE_SIZE + 1];
+                assert_eq!(writer.write(&mut reader_from(&buf)).unwrap(), PAGE_SIZE);
+                assert_eq!(writer.write(&mut reader_from(&[2])).unwrap(), 1);
+            },
+            |reader| {
+                let mut buf = vec![0; PAGE_SIZE + 1];
+                assert_eq!(reader.read(&mut writer_from(&mut buf)).unwrap(), PAGE_SIZE);
+                assert!(buf[..PAGE_SIZE].iter().all(|byte| *byte == 1));
+                assert_eq!(reader.read(&mut writer_from(&mut buf)).unwrap(), 1);
+                assert_eq!(&buf[..1], &[2]);
+            },
+            Ordering::WriteThenRead,
+        );
+    }
+
+    #[ktest]
+    fn test_read_closed() {
+        test_blocking(
+            drop,
+            |reader| {
+                let mut buf = [0; 1];
+                assert_eq!(reader.read(&mut writer_from(&mut buf)).unwrap(), 0);
+            },
+            Ordering::ReadThenWrite,
+        );
+    }
+
+    #[ktest]
+    fn test_write_closed() {
+        test_blocking(
+            |writer| {
+                let buf = vec![1; PAGE_SIZE + 1];
+                assert_eq!(writer.write(&mut reader_from(&buf)).unwrap(), PAGE_SIZE);
+                assert_eq!(
+                    writer.write(&mut reader_from(&[2])).unwrap_err().error(),
+                    Errno::EPIPE
+                );
+            },
+            drop,
+            Ordering::WriteThenRead,
+        );
+    }
+
+    #[ktest]
+    fn test_write_atomicity() {
+        test_blocking(
+            |writer| {
+                let buf = vec![2; PIPE_BUF];
+                assert_eq!(writer.write(&mut reader_from(&[1])).unwrap(), 1);
+                assert_eq!(writer.write(&mut reader_from(&buf)).unwrap(), PIPE_BUF);
+            },
+            |reader| {
+                let mut buf = vec![0; PIPE_BUF + 1];
+                assert_eq!(reader.read(&mut writer_from(&mut buf)).unwrap(), 1);
+                assert_eq!(&buf[..1], &[1]);
+                assert_eq!(reader.read(&mut writer_from(&mut buf)).unwrap(), PIPE_BUF);
+                assert!(buf[..PIPE_BUF].iter().all(|byte| *byte == 2));
+            },
+            Ordering::WriteThenRead,
+        );
+    }
+
+    #[ktest]
+    fn test_packet_mode() {
+        test_blocking(
+            |writer| {
+                writer.set_status_flags(StatusFlags::O_DIRECT).unwrap();
+                assert_eq!(writer.write(&mut reader_from(&[1, 2])).unwrap(), 2);
+                assert_eq!(writer.write(&mut reader_from(&[3])).unwrap(), 1);
+            },
+            |reader| {
+                let mut buf = [0; 3];
+                assert_eq!(reader.read(&mut writer_from(&mut buf)).unwrap(), 2);
+                assert_eq!(&buf[..2], &[1, 2]);
+                assert_eq!(reader.read(&mut writer_from(&mut buf)).unwrap(), 1);
+                assert_eq!(&buf[..1], &[3]);
+            },
+            Ordering::WriteThenRead,
+        );
+    }
+
+    fn reader_from(buf: &[u8]) -> VmReader {
+        VmReader::from(buf).to_fallible()
+    }
+
+    fn writer_from(buf: &mut [u8]) -> VmWriter {
+        VmWriter::from(buf).to_fallible()
+    }
+}
@@ -0,0 +1,249 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::collections::VecDeque;
+
+use ostd::mm::{Frame, FrameAllocOptions, Infallible, UntypedMem};
+
+use crate::prelude::*;
+
+/// A ring of page buffers, which holds the data of a pipe.
+///
+/// Each buffer holds the data of at most one page. The data written in the byte
+/// mode are merged into the last buffer if possible, while the data written in the
+/// packet mode (i.e. with `O_DIRECT`) always occupy new buffers, each of which is a
+/// packet that is read as a whole.
+pub(super) struct PipeRing {
+    bufs: VecDeque<PipeBuf>,
+    max_bufs: usize,
+}
+
+struct PipeBuf {
+    page: Frame<()>,
+    /// The offset of the data in the page.
+    offset: usize,
+    len: usize,
+    is_packet: bool,
+}
+
+impl PipeRing {
+    pub(super) fn new(max_bufs: usize) -> Self {
+        debug_assert!(max_bufs > 0);
+        Self {
+            bufs: VecDeque::new(),
+            max_bufs,
+        }
+    }
+
+    /// Returns the capacity in bytes.
+    pub(super) fn capacity(&self) -> usize {
+        self.max_bufs * PAGE_SIZE
+    }
+
+    /// Changes the maximum number of buffers.
+    ///
+    /// It fails with `EBUSY` if more buffers are being used.
+    pub(super) fn set_max_bufs(&mut self, max_bufs: usize) -> Result<()> {
+        debug_assert!(max_bufs > 0);
+        if self.bufs.len() > max_bufs {
+            return_errno_with_message!(Errno::EBUSY, "the data in the pipe exceed the new size");
+        }
+        self.max_bufs = max_bufs;
+        Ok(())
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.bufs.is_empty()
+    }
+
+    /// Returns whether a new buffer can be used, in which case at least `PAGE_SIZE`
+    /// bytes can be written.
+    pub(super) fn has_free_buf(&self) -> bool {
+        self.bufs.len() < self.max_bufs
+    }
+
+    /// Returns the number of bytes that can be written in the given mode.
+    pub(super) fn free_len(&self, is_packet: bool) -> usize {
+        let tail_room = match self.bufs.back() {
+            Some(buf) if !is_packet && !buf.is_packet => PAGE_SIZE - buf.offset - buf.len,
+            _ => 0,
+        };
+        tail_room + (self.max_bufs - self.bufs.len()) * PAGE_SIZE
+    }
+
+    /// Writes at most `max_len` bytes by `write_fn`.
+    ///
+    /// The `write_fn` is given the `VmWriter`s of the free space one by one, and returns
+    /// the number of bytes written to each of them. The writing stops once `write_fn`
+    /// writes less than the available space.
+    ///
+    /// Returns the number of bytes written.
+    pub(super) fn write_with<F>(
+        &mut self,
+        max_len: usize,
+        is_packet: bool,
+        mut write_fn: F,
+    ) -> Result<usize>
+    where
+        F: FnMut(VmWriter<'_, Infallible>) -> Result<usize>,
+    {
+        let mut written_len = 0;
+
+        if let Some(buf) = self
+            .bufs
+            .back_mut()
+            .filter(|buf| !is_packet && !buf.is_packet)
+        {
+            let end = buf.offset + buf.len;
+            let room = (PAGE_SIZE - end).min(max_len);
+            if room > 0 {
+                let mut writer = buf.page.writer();
+                writer.skip(end).limit(room);
+                let len = write_fn(writer)?;
+                buf.len += len;
+                written_len += len;
+                if len < room {
+                    return Ok(written_len);
+                }
+            }
+        }
+
+        while written_len < max_len && self.has_free_buf() {
+            let page = match FrameAllocOptions::new().zeroed(false).alloc_frame() {
+                Ok(page) => page,
+                Err(_) if written_len > 0 => break,
+                Err(err) => return Err(err.into()),
+            };
+
+            let room = PAGE_SIZE.min(max_len - written_len);
+            let mut writer = page.writer();
+            writer.limit(room);
+            let len = match write_fn(writer) {
+                Ok(len) => len,
+                Err(_) if written_len > 0 => break,
+                Err(err) => return Err(err),
+            };
+            if len == 0 {
+                break;
+            }
+
+            self.bufs.push_back(PipeBuf {
+                page,
+                offset: 0,
+                len,
+                is_packet,
+            });
+            written_len += len;
+            if len < room {
+                break;
+            }
+        }
+
+        Ok(written_len)
+    }
+
+    /// Reads at most `max_len` bytes by `read_fn`.
+    ///
+    /// The `read_fn` is given the `VmReader`s of the data one by one, and returns the
+    /// number of bytes consumed from each of them. The reading stops once `read_fn`
+    /// consumes less than the available data.
+    ///
+    /// A read never crosses the boundary of a packet. If a packet is not consumed
+    /// completely, the rest of it is discarded.
+    ///
+    /// Returns the number of bytes read.
+    pub(super) fn read_with<F>(&mut self, max_len: usize, mut read_fn: F) -> Result<usize>
+    where
+        F: FnMut(VmReader<'_, Infallible>) -> Result<usize>,
+    {
+        let mut read_len = 0;
+
+        while read_len < max_len {
+            let Some(buf) = self.bufs.front_mut() else {
+                break;
+            };
+            if buf.is_packet && read_len > 0 {
+                break;
+            }
+
+            let len = buf.len.min(max_len - read_len);
+            let mut reader = buf.page.reader();
+            reader.skip(buf.offset).limit(len);
+            let consumed_len = match read_fn(reader) {
+                Ok(len) => len,
+                Err(_) if read_len > 0 => break,
+                Err(err) => return Err(err),
+            };
+            read_len += consumed_len;
+
+            if buf.is_packet {
+                self.bufs.pop_front();
+                break;
+            }
+
+            buf.offset += consumed_len;
+            buf.len -= consumed_len;
+            if buf.len == 0 {
+                self.bufs.pop_front();
+            }
+            if consumed_len < len {
+                break;
+            }
+        }
+
+        Ok(read_len)
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    fn write_bytes(ring: &mut PipeRing, buf: &[u8], is_packet: bool) -> usize {
+        let mut reader = VmReader::from(buf);
+        ring.write_with(buf.len(), is_packet, |mut writer| {
+            Ok(writer.write(&mut reader))
+        })
+        .unwrap()
+    }
+
+    fn read_bytes(ring: &mut PipeRing, buf: &mut [u8]) -> usize {
+        let max_len = buf.len();
+        let mut writer = VmWriter::from(buf);
+        ring.read_with(max_len, |mut reader| Ok(writer.write(&mut reader)))
+            .unwrap()
+    }
+
+    #[ktest]
+    fn merge_bytes() {
+        let mut ring = PipeRing::new(2);
+        assert_eq!(write_bytes(&mut ring, &[1, 2], false), 2);
+        assert_eq!(write_bytes(&mut ring, &[3], false), 1);
+        assert_eq!(ring.free_len(false), 2 * PAGE_SIZE - 3);
+        assert!(ring.set_max_bufs(1).is_ok());
+        assert!(!ring.has_free_buf());
+
+        let mut buf = [0u8; 4];
+        assert_eq!(read_bytes(&mut ring, &mut buf), 3);
+        assert_eq!(buf[..3], [1, 2, 3]);
+        assert!(ring.is_empty());
+    }
+
+    #[ktest]
+    fn read_packets() {
+        let mut ring = PipeRing::new(4);
+        assert_eq!(write_bytes(&mut ring, &[1, 2, 3], true), 3);
+        assert_eq!(write_bytes(&mut ring, &[4], true), 1);
+        assert_eq!(ring.free_len(false), 2 * PAGE_SIZE);
+        assert!(ring.set_max_bufs(1).is_err());
+
+        // The rest of the first packet is discarded.
+        let mut buf = [0u8; 2];
+        assert_eq!(read_bytes(&mut ring, &mut buf), 2);
+        assert_eq!(buf, [1, 2]);
+        assert_eq!(read_bytes(&mut ring, &mut buf), 1);
+        assert_eq!(buf[0], 4);
+        assert!(ring.is_empty());
+    }
+}
@@ -0,0 +1,42 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! This module offers `/proc/boot_params` file support, which lists the boot-time parameters of
+//! the subsystems with their current values, one `module.name=value` in each line.
+//!
+//! The parameters are set in the kernel command line. See [`ostd::boot::param`] for details.
+
+use core::fmt::Write;
+
+use ostd::boot::param;
+
+use crate::{
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::Inode,
+    },
+    prelude::*,
+};
+
+/// Represents the inode at `/proc/boot_params`.
+pub struct BootParamsFileOps;
+
+impl BootParamsFileOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self).parent(parent).build().unwrap()
+    }
+}
+
+impl FileOps for BootParamsFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let mut params = param::params();
+        params.sort_by_key(|param| (param.module(), param.name()));
+
+        let mut output = String::new();
+        for param in params {
+            let _ = write!(output, "{}.{}=", param.module(), param.name());
+            let _ = param.fmt_value(&mut output);
+            output.push('\n');
+        }
+        Ok(output.into_bytes())
+    }
+}
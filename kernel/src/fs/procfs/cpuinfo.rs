@@ -5,10 +5,11 @@
 //!
 //! Reference: <https://man7.org/linux/man-pages/man5/proc_cpuinfo.5.html>
 
-use ostd::cpu::num_cpus;
+use ostd::cpu::all_cpus;
 
 use crate::{
     arch::cpu::CpuInfo,
+    cpu::ArchCpuInfo,
     fs::{
         procfs::template::{FileOps, ProcFileBuilder},
         utils::Inode,
@@ -25,18 +26,13 @@ impl CpuInfoFileOps {
         ProcFileBuilder::new(Self).parent(parent).build().unwrap()
     }
 
-    /// Collect and format CPU information for all cores.
+    /// Collect and format CPU information for all online CPUs.
+    ///
+    /// Each entry is followed by an empty line, as Linux does.
     fn collect_cpu_info() -> String {
-        let num_cpus = num_cpus() as u32;
-
-        // Iterate over each core and collect CPU information
-        (0..num_cpus)
-            .map(|core_id| {
-                let cpuinfo = CpuInfo::new(core_id);
-                cpuinfo.collect_cpu_info()
-            })
-            .collect::<Vec<String>>()
-            .join("\n\n")
+        all_cpus()
+            .map(|cpu| CpuInfo::new(cpu).render_procfs() + "\n")
+            .collect()
     }
 }
 
@@ -0,0 +1,66 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! This module offers `/proc/crypto` file support, which lists the drivers
+//! registered in the crypto API, in the same format as Linux.
+//!
+//! Reference: <https://www.kernel.org/doc/html/latest/crypto/userspace-if.html>
+
+use alloc::format;
+use core::fmt::Write;
+
+use aster_crypto::api::{self, Algorithm};
+
+use crate::{
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::Inode,
+    },
+    prelude::*,
+};
+
+/// Represents the inode at `/proc/crypto`.
+pub struct CryptoFileOps;
+
+impl CryptoFileOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self).parent(parent).build().unwrap()
+    }
+}
+
+impl FileOps for CryptoFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let mut output = String::new();
+        for driver in api::drivers() {
+            let _ = writeln!(output, "name         : {}", driver.name);
+            let _ = writeln!(output, "driver       : {}", driver.driver_name);
+            let _ = writeln!(output, "module       : kernel");
+            let _ = writeln!(output, "priority     : {}", driver.priority);
+            let _ = writeln!(output, "type         : {}", driver.algorithm.type_name());
+
+            let details = match &driver.algorithm {
+                Algorithm::Hash(hash) => format!(
+                    "blocksize    : {}\ndigestsize   : {}\n",
+                    hash.block_len(),
+                    hash.digest_len()
+                ),
+                Algorithm::BlockCipher(cipher) => {
+                    let key_lens = cipher.key_lens();
+                    format!(
+                        "blocksize    : {}\nmin keysize  : {}\nmax keysize  : {}\n",
+                        cipher.block_len(),
+                        key_lens.iter().min().unwrap(),
+                        key_lens.iter().max().unwrap()
+                    )
+                }
+                Algorithm::Aead(aead) => format!(
+                    "blocksize    : 1\nivsize       : {}\nmaxauthsize  : {}\n",
+                    aead.nonce_len(),
+                    aead.tag_len()
+                ),
+            };
+            output.push_str(&details);
+            output.push('\n');
+        }
+        Ok(output.into_bytes())
+    }
+}
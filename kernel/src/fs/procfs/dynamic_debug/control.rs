@@ -0,0 +1,95 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! This module offers `/proc/dynamic_debug/control` file support, which controls the log
+//! levels of the kernel modules at runtime.
+//!
+//! Unlike Linux, which controls each `pr_debug` call site, the log levels are controlled per
+//! module. Each command has the form of `module <path> <flags>`, where `<path>` is a module
+//! path (e.g., `aster_nix::fs`) and `<flags>` is one of the following:
+//!  - `+p`: enables all messages of the module;
+//!  - `-p`: restores the global log level for the module;
+//!  - `=<level>`: sets the log level (e.g., `=debug` or `=off`) of the module.
+//!
+//! Multiple commands can be separated by newlines or semicolons.
+//!
+//! Reference: <https://www.kernel.org/doc/html/latest/admin-guide/dynamic-debug-howto.html>
+
+use core::{fmt::Write, str::FromStr};
+
+use log::LevelFilter;
+
+use crate::{
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::{Inode, InodeMode},
+    },
+    prelude::*,
+};
+
+/// Represents the inode at `/proc/dynamic_debug/control`.
+pub struct ControlFileOps;
+
+impl ControlFileOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        let inode = ProcFileBuilder::new(Self).parent(parent).build().unwrap();
+        inode
+            .set_mode(InodeMode::from_bits_truncate(0o644))
+            .unwrap();
+        inode
+    }
+}
+
+impl FileOps for ControlFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let mut output = String::new();
+        let _ = writeln!(output, "# default ={}", aster_logger::default_level());
+        for (module, level) in aster_logger::module_levels() {
+            let _ = writeln!(output, "{} ={}", module, level);
+        }
+        Ok(output.into_bytes())
+    }
+
+    fn write_at(&self, _offset: usize, reader: &mut VmReader) -> Result<usize> {
+        let len = reader.remain();
+        let mut buf = vec![0u8; len];
+        reader.read_fallible(&mut VmWriter::from(buf.as_mut_slice()))?;
+
+        let commands = core::str::from_utf8(&buf)
+            .map_err(|_| Error::with_message(Errno::EINVAL, "the commands are not valid UTF-8"))?;
+        // Parse all commands before applying any of them.
+        let commands = commands
+            .split(['\n', ';'])
+            .map(str::trim)
+            .filter(|command| !command.is_empty() && !command.starts_with('#'))
+            .map(parse_command)
+            .collect::<Result<Vec<_>>>()?;
+        for (module, level) in commands {
+            aster_logger::set_module_level(module, level);
+        }
+
+        Ok(len)
+    }
+}
+
+/// Parses a command into the module path and its new log level.
+fn parse_command(command: &str) -> Result<(&str, Option<LevelFilter>)> {
+    let mut words = command.split_whitespace();
+    let (Some("module"), Some(module), Some(flags), None) =
+        (words.next(), words.next(), words.next(), words.next())
+    else {
+        return_errno_with_message!(Errno::EINVAL, "the command is invalid");
+    };
+
+    let level = match flags {
+        "+p" | "=p" => Some(LevelFilter::Trace),
+        "-p" => None,
+        _ => {
+            let level = flags
+                .strip_prefix('=')
+                .and_then(|level| LevelFilter::from_str(level).ok())
+                .ok_or_else(|| Error::with_message(Errno::EINVAL, "the flags are invalid"))?;
+            Some(level)
+        }
+    };
+    Ok((module, level))
+}
@@ -0,0 +1,56 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! This module offers `/proc/interrupts` file support, which tells the user space
+//! about the number of the interrupts dispatched to each IRQ line on each CPU.
+//!
+//! Only the IRQ lines that have received interrupts are listed.
+//!
+//! Reference: <https://man7.org/linux/man-pages/man5/proc_interrupts.5.html>
+
+use core::fmt::Write;
+
+use ostd::{cpu::all_cpus, trap::irq_count};
+
+use crate::{
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::Inode,
+    },
+    prelude::*,
+};
+
+/// Represents the inode at `/proc/interrupts`.
+pub struct InterruptsFileOps;
+
+impl InterruptsFileOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self).parent(parent).build().unwrap()
+    }
+}
+
+impl FileOps for InterruptsFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let mut output = String::new();
+
+        // The columns are aligned as Linux does.
+        write!(output, "{:11}", "").unwrap();
+        for cpu in all_cpus() {
+            write!(output, "CPU{:<8}", cpu.as_usize()).unwrap();
+        }
+        output.push('\n');
+
+        for irq_num in 0..=u8::MAX {
+            let counts: Vec<u64> = all_cpus().map(|cpu| irq_count(irq_num, cpu)).collect();
+            if counts.iter().all(|count| *count == 0) {
+                continue;
+            }
+            write!(output, "{:>3}: ", irq_num).unwrap();
+            for count in counts {
+                write!(output, "{:>10} ", count).unwrap();
+            }
+            output.push('\n');
+        }
+
+        Ok(output.into_bytes())
+    }
+}
@@ -0,0 +1,45 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! This module offers `/proc/iomem` file support, which tells the user space
+//! about the MMIO regions claimed by the device drivers and their owners.
+//!
+//! Reference: <https://man7.org/linux/man-pages/man5/proc_iomem.5.html>
+
+use core::fmt::Write;
+
+use ostd::io::claimed_regions;
+
+use crate::{
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::Inode,
+    },
+    prelude::*,
+};
+
+/// Represents the inode at `/proc/iomem`.
+pub struct IoMemFileOps;
+
+impl IoMemFileOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self).parent(parent).build().unwrap()
+    }
+}
+
+impl FileOps for IoMemFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let mut output = String::new();
+        for (range, owner) in claimed_regions() {
+            // The end addresses are inclusive, as Linux does.
+            writeln!(
+                output,
+                "{:08x}-{:08x} : {}",
+                range.start,
+                range.end - 1,
+                owner
+            )
+            .unwrap();
+        }
+        Ok(output.into_bytes())
+    }
+}
@@ -0,0 +1,109 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! This module offers `/proc/kallsyms` file support, which lists the symbols of the kernel
+//! and the loaded kernel extensions (see [`ostd::kallsyms`]).
+//!
+//! Like Linux with `kptr_restrict` set, the addresses are shown as zeros unless the reader
+//! has `CAP_SYSLOG`.
+//!
+//! Reference: <https://man7.org/linux/man-pages/man5/proc.5.html>
+
+use core::fmt::Write;
+
+use crate::{
+    events::IoEvents,
+    fs::{
+        inode_handle::FileIo,
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::{Inode, SeekFrom},
+    },
+    prelude::*,
+    process::{
+        credentials::capabilities::CapSet,
+        posix_thread::AsPosixThread,
+        signal::{PollHandle, Pollable},
+    },
+};
+
+/// Represents the inode at `/proc/kallsyms`.
+pub struct KallsymsFileOps;
+
+impl KallsymsFileOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self).parent(parent).build().unwrap()
+    }
+}
+
+impl FileOps for KallsymsFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let shows_addr = current_thread!()
+            .as_posix_thread()
+            .unwrap()
+            .credentials()
+            .effective_capset()
+            .contains(CapSet::SYSLOG);
+
+        let mut output = String::new();
+        ostd::kallsyms::for_each_symbol(|symbol| {
+            let addr = if shows_addr { symbol.addr() } else { 0 };
+            let _ = write!(output, "{:016x} {} {}", addr, symbol.kind(), symbol.name());
+            if let Some(module_name) = symbol.module_name() {
+                let _ = write!(output, "\t[{}]", module_name);
+            }
+            output.push('\n');
+        });
+        Ok(output.into_bytes())
+    }
+
+    fn open(&self) -> Result<Option<Arc<dyn FileIo>>> {
+        // The file is large, so it is generated once when opened instead of at each read.
+        Ok(Some(Arc::new(KallsymsFile {
+            data: self.data()?,
+            offset: Mutex::new(0),
+        })))
+    }
+}
+
+/// An opened `/proc/kallsyms`, which holds a snapshot of the symbols.
+struct KallsymsFile {
+    data: Vec<u8>,
+    offset: Mutex<usize>,
+}
+
+impl Pollable for KallsymsFile {
+    fn poll(&self, mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        (IoEvents::IN | IoEvents::OUT) & mask
+    }
+}
+
+impl FileIo for KallsymsFile {
+    fn read(&self, writer: &mut VmWriter) -> Result<usize> {
+        let mut offset = self.offset.lock();
+        let start = self.data.len().min(*offset);
+        let end = self.data.len().min(start + writer.avail());
+        writer.write_fallible(&mut (&self.data[start..end]).into())?;
+        *offset = end;
+        Ok(end - start)
+    }
+
+    fn write(&self, _reader: &mut VmReader) -> Result<usize> {
+        return_errno_with_message!(Errno::EPERM, "`/proc/kallsyms` is read-only");
+    }
+
+    fn seek(&self, pos: SeekFrom) -> Option<Result<usize>> {
+        let mut offset = self.offset.lock();
+        let new_offset = match pos {
+            SeekFrom::Start(off) => Some(off),
+            SeekFrom::End(off) => self.data.len().checked_add_signed(off),
+            SeekFrom::Current(off) => offset.checked_add_signed(off),
+        };
+        let Some(new_offset) = new_offset.filter(|off| *off <= isize::MAX as usize) else {
+            return Some(Err(Error::with_message(
+                Errno::EINVAL,
+                "the file offset is invalid",
+            )));
+        };
+        *offset = new_offset;
+        Some(Ok(new_offset))
+    }
+}
@@ -14,6 +14,7 @@ use crate::{
         utils::Inode,
     },
     prelude::*,
+    vm::hugetlb::{HugePagePool, HugePageSize},
 };
 
 /// Represents the inode at `/proc/meminfo`.
@@ -32,15 +33,34 @@ impl FileOps for MemInfoFileOps {
         // An estimation of how much memory is available for starting new
         // applications, without disk operations.
         let available = osdk_frame_allocator::load_total_free_size();
+        // The memory that is locked by `mlock` and similar system calls.
+        let mlocked = crate::vm::vmar::total_locked_size();
 
         // Convert the values to KiB.
         let total = total / 1024;
         let available = available / 1024;
         let free = total - available;
-        let output = format!(
-            "MemTotal:\t{} kB\nMemFree:\t{} kB\nMemAvailable:\t{} kB\n",
-            total, free, available
+        let mlocked = mlocked / 1024;
+        let mut output = format!(
+            "MemTotal:\t{} kB\nMemFree:\t{} kB\nMemAvailable:\t{} kB\nMlocked:\t{} kB\n",
+            total, free, available, mlocked
         );
+
+        // The huge pages of the default size, and the memory of the huge pages of all sizes.
+        let stats = HugePagePool::get(HugePageSize::DEFAULT).stats();
+        let hugetlb: usize = HugePageSize::ALL
+            .into_iter()
+            .map(|size| HugePagePool::get(size).stats().nr_total * size.bytes())
+            .sum();
+        output.push_str(&format!(
+            "HugePages_Total:\t{}\nHugePages_Free:\t{}\nHugePages_Rsvd:\t{}\nHugePages_Surp:\t0\nHugepagesize:\t{} kB\nHugetlb:\t{} kB\n",
+            stats.nr_total,
+            stats.nr_free,
+            stats.nr_reserved,
+            HugePageSize::DEFAULT.bytes() / 1024,
+            hugetlb / 1024
+        ));
+
         Ok(output.into_bytes())
     }
 }
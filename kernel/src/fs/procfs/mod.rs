@@ -4,12 +4,24 @@ use core::sync::atomic::{AtomicU64, Ordering};
 
 use filesystems::{FileSystemType, FILESYSTEM_TYPES};
 
+#[cfg(target_arch = "riscv64")]
+use self::vmcore::VmcoreFileOps;
 use self::{
+    boot_params::BootParamsFileOps,
     cpuinfo::CpuInfoFileOps,
+    crypto::CryptoFileOps,
+    dynamic_debug::DynamicDebugDirOps,
+    interrupts::InterruptsFileOps,
+    iomem::IoMemFileOps,
+    kallsyms::KallsymsFileOps,
     loadavg::LoadAvgFileOps,
     meminfo::MemInfoFileOps,
+    mtd::MtdFileOps,
     pid::PidDirOps,
+    pressure::PressureDirOps,
+    schedstat::SchedStatFileOps,
     self_::SelfSymOps,
+    stat::StatFileOps,
     sys::SysDirOps,
     template::{DirOps, ProcDir, ProcDirBuilder, ProcSymBuilder, SymOps},
     thread_self::ThreadSelfSymOps,
@@ -27,32 +39,58 @@ use crate::{
     },
 };
 
+mod boot_params;
 mod cpuinfo;
+mod crypto;
+mod dynamic_debug;
 mod filesystems;
+mod interrupts;
+mod iomem;
+mod kallsyms;
 mod loadavg;
 mod meminfo;
+mod mtd;
 mod pid;
+mod pressure;
+mod schedstat;
 mod self_;
+mod stat;
 mod sys;
-mod template;
+pub(in crate::fs) mod template;
 mod thread_self;
+#[cfg(target_arch = "riscv64")]
+mod vmcore;
 
 pub(super) fn init() {
     FILESYSTEM_TYPES.call_once(|| {
-        vec![
+        #[cfg_attr(not(target_arch = "riscv64"), expect(unused_mut))]
+        let mut types = vec![
             FileSystemType::new("proc", true),
             FileSystemType::new("ramfs", true),
             FileSystemType::new("devpts", true),
+            FileSystemType::new("overlay", true),
+            FileSystemType::new("tmpfs", true),
+            FileSystemType::new("mqueue", true),
+            FileSystemType::new("tracefs", true),
+            FileSystemType::new("cgroup2", true),
+            FileSystemType::new("hugetlbfs", true),
             FileSystemType::new("ext2", false),
+            FileSystemType::new("ext3", false),
+            FileSystemType::new("ext4", false),
             FileSystemType::new("exfat", false),
-        ]
+            FileSystemType::new("vfat", false),
+            FileSystemType::new("flashfs", false),
+        ];
+        #[cfg(target_arch = "riscv64")]
+        types.push(FileSystemType::new("pstore", true));
+        types
     });
 }
 
 /// Magic number.
 const PROC_MAGIC: u64 = 0x9fa0;
 /// Root Inode ID.
-const PROC_ROOT_INO: u64 = 1;
+pub(in crate::fs) const PROC_ROOT_INO: u64 = 1;
 /// Block size.
 const BLOCK_SIZE: usize = 1024;
 
@@ -64,9 +102,20 @@ pub struct ProcFS {
 
 impl ProcFS {
     pub fn new() -> Arc<Self> {
+        Self::new_with_root(PROC_MAGIC, RootDirOps::new_inode)
+    }
+
+    /// Creates a filesystem whose inodes are built from the templates of procfs.
+    ///
+    /// This allows other pseudo filesystems (e.g., the tracefs) to reuse the templates. The
+    /// root inode should be built with the ID [`PROC_ROOT_INO`].
+    pub(in crate::fs) fn new_with_root(
+        magic: u64,
+        new_root: fn(Weak<ProcFS>) -> Arc<dyn Inode>,
+    ) -> Arc<Self> {
         Arc::new_cyclic(|weak_fs| Self {
-            sb: SuperBlock::new(PROC_MAGIC, BLOCK_SIZE, NAME_MAX),
-            root: RootDirOps::new_inode(weak_fs.clone()),
+            sb: SuperBlock::new(magic, BLOCK_SIZE, NAME_MAX),
+            root: new_root(weak_fs.clone()),
             inode_allocator: AtomicU64::new(PROC_ROOT_INO + 1),
         })
     }
@@ -120,6 +169,12 @@ impl Observer<PidEvent> for ProcDir<RootDirOps> {
 
 impl DirOps for RootDirOps {
     fn lookup_child(&self, this_ptr: Weak<dyn Inode>, name: &str) -> Result<Arc<dyn Inode>> {
+        #[cfg(target_arch = "riscv64")]
+        if name == "vmcore" {
+            let vmcore = crate::kexec::vmcore::vmcore().ok_or_else(|| Error::new(Errno::ENOENT))?;
+            return Ok(VmcoreFileOps::new_inode(vmcore, this_ptr));
+        }
+
         let child = if name == "self" {
             SelfSymOps::new_inode(this_ptr.clone())
         } else if name == "sys" {
@@ -134,6 +189,26 @@ impl DirOps for RootDirOps {
             LoadAvgFileOps::new_inode(this_ptr.clone())
         } else if name == "cpuinfo" {
             CpuInfoFileOps::new_inode(this_ptr.clone())
+        } else if name == "boot_params" {
+            BootParamsFileOps::new_inode(this_ptr.clone())
+        } else if name == "crypto" {
+            CryptoFileOps::new_inode(this_ptr.clone())
+        } else if name == "dynamic_debug" {
+            DynamicDebugDirOps::new_inode(this_ptr.clone())
+        } else if name == "interrupts" {
+            InterruptsFileOps::new_inode(this_ptr.clone())
+        } else if name == "iomem" {
+            IoMemFileOps::new_inode(this_ptr.clone())
+        } else if name == "kallsyms" {
+            KallsymsFileOps::new_inode(this_ptr.clone())
+        } else if name == "mtd" {
+            MtdFileOps::new_inode(this_ptr.clone())
+        } else if name == "stat" {
+            StatFileOps::new_inode(this_ptr.clone())
+        } else if name == "schedstat" {
+            SchedStatFileOps::new_inode(this_ptr.clone())
+        } else if name == "pressure" {
+            PressureDirOps::new_inode(this_ptr.clone())
         } else if let Ok(pid) = name.parse::<Pid>() {
             let process_ref =
                 process_table::get_process(pid).ok_or_else(|| Error::new(Errno::ENOENT))?;
@@ -164,6 +239,34 @@ impl DirOps for RootDirOps {
             .put_entry_if_not_found("loadavg", || LoadAvgFileOps::new_inode(this_ptr.clone()));
         cached_children
             .put_entry_if_not_found("cpuinfo", || CpuInfoFileOps::new_inode(this_ptr.clone()));
+        cached_children.put_entry_if_not_found("boot_params", || {
+            BootParamsFileOps::new_inode(this_ptr.clone())
+        });
+        cached_children
+            .put_entry_if_not_found("crypto", || CryptoFileOps::new_inode(this_ptr.clone()));
+        cached_children.put_entry_if_not_found("dynamic_debug", || {
+            DynamicDebugDirOps::new_inode(this_ptr.clone())
+        });
+        cached_children.put_entry_if_not_found("interrupts", || {
+            InterruptsFileOps::new_inode(this_ptr.clone())
+        });
+        cached_children
+            .put_entry_if_not_found("iomem", || IoMemFileOps::new_inode(this_ptr.clone()));
+        cached_children
+            .put_entry_if_not_found("kallsyms", || KallsymsFileOps::new_inode(this_ptr.clone()));
+        cached_children.put_entry_if_not_found("mtd", || MtdFileOps::new_inode(this_ptr.clone()));
+        cached_children.put_entry_if_not_found("stat", || StatFileOps::new_inode(this_ptr.clone()));
+        cached_children.put_entry_if_not_found("schedstat", || {
+            SchedStatFileOps::new_inode(this_ptr.clone())
+        });
+        cached_children
+            .put_entry_if_not_found("pressure", || PressureDirOps::new_inode(this_ptr.clone()));
+        #[cfg(target_arch = "riscv64")]
+        if let Some(vmcore) = crate::kexec::vmcore::vmcore() {
+            cached_children.put_entry_if_not_found("vmcore", || {
+                VmcoreFileOps::new_inode(vmcore, this_ptr.clone())
+            });
+        }
         for process in process_table::process_table_mut().iter() {
             let pid = process.pid().to_string();
             cached_children.put_entry_if_not_found(&pid, || {
@@ -0,0 +1,43 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! This module offers `/proc/mtd` file support, which tells the user space
+//! about the MTD devices, i.e., the partitions of the flashes.
+//!
+//! Reference: <https://www.kernel.org/doc/html/latest/filesystems/proc.html>
+
+use core::fmt::Write;
+
+use crate::{
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::Inode,
+    },
+    prelude::*,
+};
+
+/// Represents the inode at `/proc/mtd`.
+pub struct MtdFileOps;
+
+impl MtdFileOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self).parent(parent).build().unwrap()
+    }
+}
+
+impl FileOps for MtdFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let mut output = String::from("dev:    size   erasesize  name\n");
+        for device in aster_mtd::all_devices() {
+            writeln!(
+                output,
+                "mtd{}: {:08x} {:08x} \"{}\"",
+                device.index(),
+                device.size(),
+                device.erase_size(),
+                device.name()
+            )
+            .unwrap();
+        }
+        Ok(output.into_bytes())
+    }
+}
@@ -0,0 +1,38 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use crate::{
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::{Inode, InodeMode},
+    },
+    prelude::*,
+    Process,
+};
+
+/// Represents the inode at `/proc/[pid]/auxv`, which contains the auxiliary vector that is
+/// passed to the program (or that is set by `prctl(PR_SET_MM_AUXV)`).
+pub struct AuxvFileOps(Arc<Process>);
+
+impl AuxvFileOps {
+    pub fn new_inode(process_ref: Arc<Process>, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        let inode = ProcFileBuilder::new(Self(process_ref))
+            .parent(parent)
+            .build()
+            .unwrap();
+        inode
+            .set_mode(InodeMode::from_bits_truncate(0o400))
+            .unwrap();
+        inode
+    }
+}
+
+impl FileOps for AuxvFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let mm_layout = self.0.vm().mm_layout().lock();
+        Ok(mm_layout
+            .auxv
+            .iter()
+            .flat_map(|word| word.to_ne_bytes())
+            .collect())
+    }
+}
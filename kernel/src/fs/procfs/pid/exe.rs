@@ -25,4 +25,8 @@ impl SymOps for ExeSymOps {
     fn read_link(&self) -> Result<String> {
         Ok(self.0.executable_path())
     }
+
+    fn is_magic_link(&self) -> bool {
+        true
+    }
 }
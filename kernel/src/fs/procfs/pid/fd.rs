@@ -99,4 +99,8 @@ impl SymOps for FileSymOps {
         };
         Ok(path)
     }
+
+    fn is_magic_link(&self) -> bool {
+        true
+    }
 }
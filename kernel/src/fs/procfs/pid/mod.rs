@@ -1,7 +1,16 @@
 // SPDX-License-Identifier: MPL-2.0
 
 use self::{
-    cmdline::CmdlineFileOps, comm::CommFileOps, exe::ExeSymOps, fd::FdDirOps, task::TaskDirOps,
+    auxv::AuxvFileOps,
+    cgroup::CgroupFileOps,
+    cmdline::CmdlineFileOps,
+    comm::CommFileOps,
+    exe::ExeSymOps,
+    fd::FdDirOps,
+    pagemap::PagemapFileOps,
+    schedstat::{SchedFileOps, SchedStatFileOps},
+    strace::StraceFileOps,
+    task::TaskDirOps,
 };
 use super::template::{DirOps, ProcDir, ProcDirBuilder};
 use crate::{
@@ -14,12 +23,17 @@ use crate::{
     process::{posix_thread::AsPosixThread, Process},
 };
 
+mod auxv;
+mod cgroup;
 mod cmdline;
 mod comm;
 mod exe;
 mod fd;
+mod pagemap;
+mod schedstat;
 mod stat;
 mod status;
+mod strace;
 mod task;
 
 /// Represents the inode at `/proc/[pid]`.
@@ -57,8 +71,14 @@ impl DirOps for PidDirOps {
             "comm" => CommFileOps::new_inode(self.0.clone(), this_ptr.clone()),
             "fd" => FdDirOps::new_inode(self.0.clone(), this_ptr.clone()),
             "cmdline" => CmdlineFileOps::new_inode(self.0.clone(), this_ptr.clone()),
+            "cgroup" => CgroupFileOps::new_inode(self.0.clone(), this_ptr.clone()),
+            "auxv" => AuxvFileOps::new_inode(self.0.clone(), this_ptr.clone()),
+            "pagemap" => PagemapFileOps::new_inode(self.0.clone(), this_ptr.clone()),
             "status" => status::StatusFileOps::new_inode(self.0.clone(), this_ptr.clone()),
             "stat" => stat::StatFileOps::new_inode(self.0.clone(), this_ptr.clone()),
+            "sched" => SchedFileOps::new_inode(self.0.clone(), this_ptr.clone()),
+            "schedstat" => SchedStatFileOps::new_inode(self.0.clone(), this_ptr.clone()),
+            "strace" => StraceFileOps::new_inode(self.0.clone(), this_ptr.clone()),
             "task" => TaskDirOps::new_inode(self.0.clone(), this_ptr.clone()),
             _ => return_errno!(Errno::ENOENT),
         };
@@ -83,12 +103,30 @@ impl DirOps for PidDirOps {
         cached_children.put_entry_if_not_found("cmdline", || {
             CmdlineFileOps::new_inode(self.0.clone(), this_ptr.clone())
         });
+        cached_children.put_entry_if_not_found("cgroup", || {
+            CgroupFileOps::new_inode(self.0.clone(), this_ptr.clone())
+        });
+        cached_children.put_entry_if_not_found("auxv", || {
+            AuxvFileOps::new_inode(self.0.clone(), this_ptr.clone())
+        });
+        cached_children.put_entry_if_not_found("pagemap", || {
+            PagemapFileOps::new_inode(self.0.clone(), this_ptr.clone())
+        });
         cached_children.put_entry_if_not_found("status", || {
             status::StatusFileOps::new_inode(self.0.clone(), this_ptr.clone())
         });
         cached_children.put_entry_if_not_found("stat", || {
             stat::StatFileOps::new_inode(self.0.clone(), this_ptr.clone())
         });
+        cached_children.put_entry_if_not_found("sched", || {
+            SchedFileOps::new_inode(self.0.clone(), this_ptr.clone())
+        });
+        cached_children.put_entry_if_not_found("schedstat", || {
+            SchedStatFileOps::new_inode(self.0.clone(), this_ptr.clone())
+        });
+        cached_children.put_entry_if_not_found("strace", || {
+            StraceFileOps::new_inode(self.0.clone(), this_ptr.clone())
+        });
         cached_children.put_entry_if_not_found("task", || {
             TaskDirOps::new_inode(self.0.clone(), this_ptr.clone())
         });
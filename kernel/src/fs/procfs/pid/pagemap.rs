@@ -0,0 +1,114 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! This module offers `/proc/[pid]/pagemap`, which reports the status of each virtual page of
+//! a process as a 64-bit entry at the offset `vpn * 8`.
+//!
+//! Each entry consists of
+//! - bits 0-54: the page frame number if the page is present, which is only reported to the
+//!   readers with `CAP_SYS_ADMIN`;
+//! - bit 56: whether the page is mapped only once;
+//! - bit 61: whether the page is a page of a file or of the shared memory;
+//! - bit 63: whether the page is present.
+//!
+//! Reference: <https://docs.kernel.org/admin-guide/mm/pagemap.html>
+
+use ostd::mm::MAX_USERSPACE_VADDR;
+
+use crate::{
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::Inode,
+    },
+    prelude::*,
+    process::{credentials::capabilities::CapSet, posix_thread::AsPosixThread},
+    Process,
+};
+
+const PM_ENTRY_BYTES: usize = size_of::<u64>();
+const PM_PFRAME_MASK: u64 = (1 << 55) - 1;
+const PM_MMAP_EXCLUSIVE: u64 = 1 << 56;
+const PM_FILE: u64 = 1 << 61;
+const PM_PRESENT: u64 = 1 << 63;
+
+/// The maximum number of the pages that are queried at once.
+const MAX_PAGES_PER_WALK: usize = 512;
+
+/// Represents the inode at `/proc/[pid]/pagemap`.
+pub struct PagemapFileOps(Arc<Process>);
+
+impl PagemapFileOps {
+    pub fn new_inode(process_ref: Arc<Process>, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self(process_ref))
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for PagemapFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        return_errno_with_message!(Errno::EINVAL, "the file is too large to be read at once");
+    }
+
+    fn read_at(&self, offset: usize, writer: &mut VmWriter) -> Result<usize> {
+        if offset % PM_ENTRY_BYTES != 0 || writer.avail() % PM_ENTRY_BYTES != 0 {
+            return_errno_with_message!(Errno::EINVAL, "the entries are not read as a whole");
+        }
+
+        let shows_pfn = current_thread!()
+            .as_posix_thread()
+            .unwrap()
+            .credentials()
+            .effective_capset()
+            .contains(CapSet::SYS_ADMIN);
+
+        let end_vpn = MAX_USERSPACE_VADDR / PAGE_SIZE;
+        let mut vpn = offset / PM_ENTRY_BYTES;
+        let mut read_len = 0;
+        while vpn < end_vpn && writer.avail() > 0 {
+            let nr_pages = (writer.avail() / PM_ENTRY_BYTES)
+                .min(MAX_PAGES_PER_WALK)
+                .min(end_vpn - vpn);
+            let range = vpn * PAGE_SIZE..(vpn + nr_pages) * PAGE_SIZE;
+
+            let status = {
+                let root_vmar = self.0.lock_root_vmar();
+                let Some(root_vmar) = root_vmar.try_get() else {
+                    // The process has exited.
+                    break;
+                };
+                root_vmar.page_status(range)?
+            };
+
+            let entries: Vec<u64> = status
+                .into_iter()
+                .map(|status| {
+                    let Some(status) = status else {
+                        return 0;
+                    };
+                    let mut entry = PM_PRESENT;
+                    if shows_pfn {
+                        entry |= (status.paddr / PAGE_SIZE) as u64 & PM_PFRAME_MASK;
+                    }
+                    if status.is_exclusive {
+                        entry |= PM_MMAP_EXCLUSIVE;
+                    }
+                    if status.is_vmo_page {
+                        entry |= PM_FILE;
+                    }
+                    entry
+                })
+                .collect();
+            let bytes: Vec<u8> = entries
+                .iter()
+                .flat_map(|entry| entry.to_ne_bytes())
+                .collect();
+            writer.write_fallible(&mut bytes.as_slice().into())?;
+
+            vpn += nr_pages;
+            read_len += bytes.len();
+        }
+
+        Ok(read_len)
+    }
+}
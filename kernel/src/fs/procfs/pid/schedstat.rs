@@ -0,0 +1,83 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::format;
+
+use crate::{
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::Inode,
+    },
+    prelude::*,
+    Process,
+};
+
+/// Represents the inode at `/proc/[pid]/schedstat`.
+///
+/// The file contains the time spent on running and on waiting in the run queues (in
+/// nanoseconds), and the number of the time slices run on a CPU.
+pub struct SchedStatFileOps(Arc<Process>);
+
+impl SchedStatFileOps {
+    pub fn new_inode(process_ref: Arc<Process>, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self(process_ref))
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for SchedStatFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let stat = self.0.main_thread().sched_attr().sched_stat();
+        let output = format!(
+            "{} {} {}\n",
+            stat.run_time_ns, stat.wait_time_ns, stat.nr_timeslices
+        );
+        Ok(output.into_bytes())
+    }
+}
+
+/// Represents the inode at `/proc/[pid]/sched`.
+///
+/// The file contains the detailed scheduling statistics, one per line.
+pub struct SchedFileOps(Arc<Process>);
+
+impl SchedFileOps {
+    pub fn new_inode(process_ref: Arc<Process>, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self(process_ref))
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for SchedFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let stat = self.0.main_thread().sched_attr().sched_stat();
+        let as_ms = |ns: u64| format!("{}.{:06}", ns / 1_000_000, ns % 1_000_000);
+
+        let exe_path = self.0.executable_path();
+        let comm = exe_path.rsplit('/').next().unwrap_or(&exe_path);
+        let mut output = format!("{} ({})\n", comm, self.0.pid());
+        let fields = [
+            ("se.sum_exec_runtime", as_ms(stat.run_time_ns)),
+            ("se.statistics.wait_sum", as_ms(stat.wait_time_ns)),
+            ("se.statistics.sum_sleep_runtime", as_ms(stat.sleep_time_ns)),
+            ("se.nr_migrations", stat.nr_migrations.to_string()),
+            ("se.statistics.nr_wakeups", stat.nr_wakeups.to_string()),
+            (
+                "se.statistics.wakeup_latency_sum",
+                as_ms(stat.wakeup_latency_ns),
+            ),
+            (
+                "se.statistics.wakeup_latency_max",
+                as_ms(stat.max_wakeup_latency_ns),
+            ),
+            ("nr_switches", stat.nr_timeslices.to_string()),
+        ];
+        for (name, value) in fields {
+            output.push_str(&format!("{:<43}:{:>21}\n", name, value));
+        }
+        Ok(output.into_bytes())
+    }
+}
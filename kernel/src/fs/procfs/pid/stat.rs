@@ -1,6 +1,6 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use core::fmt::Write;
+use core::{fmt::Write, sync::atomic::Ordering};
 
 use crate::{
     fs::{
@@ -8,13 +8,15 @@ use crate::{
         utils::Inode,
     },
     prelude::*,
+    process::ResourceType,
+    time::duration_to_clock_t,
     Process,
 };
 
 /// Represents the inode at `/proc/[pid]/stat`.
 /// The fields are the same as the ones in `/proc/[pid]/status`. But the format is different.
 /// See https://github.com/torvalds/linux/blob/ce1c54fdff7c4556b08f5b875a331d8952e8b6b7/fs/proc/array.c#L467
-/// FIXME: Some fields are not implemented yet.
+/// FIXME: Some fields are not implemented yet, and they are reported as zero.
 ///
 /// Fields:
 /// - pid              : Process ID.
@@ -96,12 +98,67 @@ impl FileOps for StatFileOps {
         } else {
             0
         };
+        let session = if let Some(session) = process.session() {
+            session.sid()
+        } else {
+            0
+        };
+
+        let prof_clock = process.prof_clock();
+        let children_prof_clock = process.children_prof_clock();
+        let utime = duration_to_clock_t(prof_clock.user_clock().read_time());
+        let stime = duration_to_clock_t(prof_clock.kernel_clock().read_time());
+        let cutime = duration_to_clock_t(children_prof_clock.user_clock().read_time());
+        let cstime = duration_to_clock_t(children_prof_clock.kernel_clock().read_time());
+
+        let nice: i8 = process.nice().load(Ordering::Relaxed).into();
+        // Like Linux, the priority of a normal process is `20 + nice`.
+        let priority = 20 + nice as i32;
+        let num_threads = process.tasks().lock().as_slice().len();
+        let starttime = duration_to_clock_t(process.start_time());
+        let rsslim = process
+            .resource_limits()
+            .get_rlimit(ResourceType::RLIMIT_RSS)
+            .get_cur();
+        let exit_signal = process.exit_signal().map_or(0, |sig_num| sig_num.as_u8());
+        let mm_layout = process.vm().mm_layout().lock().clone();
+        let start_brk = process.heap().base();
+        let exit_code = if process.status().is_zombie() {
+            process.status().exit_code()
+        } else {
+            0
+        };
 
         let mut stat_output = String::new();
+        write!(
+            stat_output,
+            "{} ({}) {} {} {} {} 0 -1 0 0 0 0 0 ",
+            pid, comm, state, ppid, pgrp, session
+        )
+        .unwrap();
+        write!(
+            stat_output,
+            "{} {} {} {} {} {} {} 0 {} ",
+            utime, stime, cutime, cstime, priority, nice, num_threads, starttime
+        )
+        .unwrap();
+        write!(
+            stat_output,
+            "0 0 {} {} {} {} 0 0 0 0 0 0 0 0 0 {} 0 0 0 0 0 0 ",
+            rsslim, mm_layout.start_code, mm_layout.end_code, mm_layout.start_stack, exit_signal
+        )
+        .unwrap();
         writeln!(
             stat_output,
-            "{} ({}) {} {} {}",
-            pid, comm, state, ppid, pgrp
+            "{} {} {} {} {} {} {} {}",
+            mm_layout.start_data,
+            mm_layout.end_data,
+            start_brk,
+            mm_layout.arg_start,
+            mm_layout.arg_end,
+            mm_layout.env_start,
+            mm_layout.env_end,
+            exit_code
         )
         .unwrap();
         Ok(stat_output.into_bytes())
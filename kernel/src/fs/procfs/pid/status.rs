@@ -73,7 +73,8 @@ impl FileOps for StatusFileOps {
     fn data(&self) -> Result<Vec<u8>> {
         let process = &self.0;
         let main_thread = process.main_thread();
-        let file_table = main_thread.as_posix_thread().unwrap().file_table();
+        let posix_thread = main_thread.as_posix_thread().unwrap();
+        let file_table = posix_thread.file_table();
 
         let mut status_output = String::new();
         writeln!(status_output, "Name:\t{}", process.executable_path()).unwrap();
@@ -82,12 +83,26 @@ impl FileOps for StatusFileOps {
         writeln!(status_output, "PPid:\t{}", process.parent().pid()).unwrap();
         writeln!(status_output, "TracerPid:\t{}", process.parent().pid()).unwrap(); // Assuming TracerPid is the same as PPid
         writeln!(status_output, "FDSize:\t{}", file_table.read().len()).unwrap();
+        if let Some(root_vmar) = process.lock_root_vmar().try_get() {
+            writeln!(
+                status_output,
+                "VmLck:\t{} kB",
+                root_vmar.locked_size() / 1024
+            )
+            .unwrap();
+        }
         writeln!(
             status_output,
             "Threads:\t{}",
             process.tasks().lock().as_slice().len()
         )
         .unwrap();
+        writeln!(
+            status_output,
+            "Seccomp:\t{}",
+            posix_thread.credentials().seccomp_mode().as_u32()
+        )
+        .unwrap();
         Ok(status_output.into_bytes())
     }
 }
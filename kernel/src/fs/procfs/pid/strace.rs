@@ -0,0 +1,68 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! This module offers `/proc/[pid]/strace` file support, which toggles the in-kernel syscall
+//! tracer for the process.
+//!
+//! The file reads `1` if the syscalls of the process are logged, or `0` otherwise. Writing `1`
+//! or `0` enables or disables the tracer. The file is specific to Asterinas.
+
+use alloc::format;
+
+use crate::{
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::{Inode, InodeMode},
+    },
+    prelude::*,
+    process::{credentials::capabilities::CapSet, posix_thread::AsPosixThread},
+    Process,
+};
+
+/// Represents the inode at `/proc/[pid]/strace`.
+pub struct StraceFileOps(Arc<Process>);
+
+impl StraceFileOps {
+    pub fn new_inode(process_ref: Arc<Process>, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        let inode = ProcFileBuilder::new(Self(process_ref))
+            .parent(parent)
+            .build()
+            .unwrap();
+        inode
+            .set_mode(InodeMode::from_bits_truncate(0o644))
+            .unwrap();
+        inode
+    }
+}
+
+impl FileOps for StraceFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        Ok(format!("{}\n", self.0.is_syscall_traced() as u8).into_bytes())
+    }
+
+    fn write_at(&self, _offset: usize, reader: &mut VmReader) -> Result<usize> {
+        let len = reader.remain();
+        let mut buf = vec![0u8; len];
+        reader.read_fallible(&mut VmWriter::from(buf.as_mut_slice()))?;
+
+        let is_traced = match core::str::from_utf8(&buf).map(str::trim) {
+            Ok("0") => false,
+            Ok("1") => true,
+            _ => return_errno_with_message!(Errno::EINVAL, "the value is neither 0 nor 1"),
+        };
+
+        // Like attaching `strace(1)`, tracing another process requires `CAP_SYS_PTRACE`.
+        let is_current = Arc::ptr_eq(&self.0, &current!());
+        let has_cap = current_thread!()
+            .as_posix_thread()
+            .unwrap()
+            .credentials()
+            .effective_capset()
+            .contains(CapSet::SYS_PTRACE);
+        if !is_current && !has_cap {
+            return_errno_with_message!(Errno::EPERM, "the process cannot be traced");
+        }
+
+        self.0.set_syscall_traced(is_traced);
+        Ok(len)
+    }
+}
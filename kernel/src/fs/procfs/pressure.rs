@@ -0,0 +1,205 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! This module offers the files in `/proc/pressure`, which tell the user space about the
+//! pressure stall information (PSI) of the CPUs, memory and I/O (see
+//! [`crate::sched::pressure`]).
+//!
+//! Each file reads like
+//!
+//! ```text
+//! some avg10=0.00 avg60=0.00 avg300=0.00 total=0
+//! full avg10=0.00 avg60=0.00 avg300=0.00 total=0
+//! ```
+//!
+//! where the averages are percentages and the total is in microseconds.
+//!
+//! Writing `<some|full> <threshold> <window>` (both in microseconds) to an opened file creates a
+//! trigger, which is bound to the opened file. After the stall time in a time window exceeds
+//! the threshold, polling the file reports `POLLPRI`.
+//!
+//! Reference: <https://docs.kernel.org/accounting/psi.html>
+
+use alloc::format;
+
+use crate::{
+    events::IoEvents,
+    fs::{
+        inode_handle::FileIo,
+        procfs::template::{DirOps, FileOps, ProcDir, ProcDirBuilder, ProcFileBuilder},
+        utils::{DirEntryVecExt, Inode, InodeMode, SeekFrom},
+    },
+    prelude::*,
+    process::signal::{PollHandle, Pollable},
+    sched::pressure::{get_pressure, Resource, StallKind, Trigger},
+};
+
+/// Represents the inode at `/proc/pressure`.
+pub struct PressureDirOps;
+
+impl PressureDirOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcDirBuilder::new(Self).parent(parent).build().unwrap()
+    }
+}
+
+impl DirOps for PressureDirOps {
+    fn lookup_child(&self, this_ptr: Weak<dyn Inode>, name: &str) -> Result<Arc<dyn Inode>> {
+        let Some(resource) = Resource::ALL
+            .into_iter()
+            .find(|resource| resource.name() == name)
+        else {
+            return_errno!(Errno::ENOENT);
+        };
+        Ok(PressureFileOps::new_inode(resource, this_ptr))
+    }
+
+    fn populate_children(&self, this_ptr: Weak<dyn Inode>) {
+        let this = {
+            let this = this_ptr.upgrade().unwrap();
+            this.downcast_ref::<ProcDir<PressureDirOps>>()
+                .unwrap()
+                .this()
+        };
+        let mut cached_children = this.cached_children().write();
+        for resource in Resource::ALL {
+            cached_children.put_entry_if_not_found(resource.name(), || {
+                PressureFileOps::new_inode(resource, this_ptr.clone())
+            });
+        }
+    }
+}
+
+/// Represents the inode at `/proc/pressure/[resource]`.
+struct PressureFileOps(Resource);
+
+impl PressureFileOps {
+    fn new_inode(resource: Resource, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        let inode = ProcFileBuilder::new(Self(resource))
+            .parent(parent)
+            .build()
+            .unwrap();
+        inode
+            .set_mode(InodeMode::from_bits_truncate(0o644))
+            .unwrap();
+        inode
+    }
+}
+
+impl FileOps for PressureFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        Ok(pressure_data(self.0))
+    }
+
+    fn open(&self) -> Result<Option<Arc<dyn FileIo>>> {
+        Ok(Some(Arc::new(PressureFile {
+            resource: self.0,
+            offset: Mutex::new(0),
+            trigger: Mutex::new(None),
+        })))
+    }
+}
+
+fn pressure_data(resource: Resource) -> Vec<u8> {
+    let mut output = String::new();
+    for (name, kind) in [("some", StallKind::Some), ("full", StallKind::Full)] {
+        let pressure = get_pressure(resource, kind);
+        output.push_str(&format!(
+            "{} avg10={:.2} avg60={:.2} avg300={:.2} total={}\n",
+            name,
+            pressure.avg[0],
+            pressure.avg[1],
+            pressure.avg[2],
+            pressure.total_ns / 1000,
+        ));
+    }
+    output.into_bytes()
+}
+
+/// An opened file in `/proc/pressure`, which owns the trigger created with it.
+struct PressureFile {
+    resource: Resource,
+    offset: Mutex<usize>,
+    trigger: Mutex<Option<Arc<Trigger>>>,
+}
+
+impl Pollable for PressureFile {
+    fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
+        let trigger = self.trigger.lock().clone();
+        if let Some(trigger) = trigger {
+            return trigger.poll(mask, poller);
+        }
+
+        // Like Linux, the files without triggers always report errors for `POLLPRI`.
+        let events = IoEvents::IN | IoEvents::OUT | IoEvents::ERR | IoEvents::PRI;
+        events & mask
+    }
+}
+
+impl FileIo for PressureFile {
+    fn read(&self, writer: &mut VmWriter) -> Result<usize> {
+        let data = pressure_data(self.resource);
+
+        let mut offset = self.offset.lock();
+        let start = data.len().min(*offset);
+        let end = data.len().min(start + writer.avail());
+        writer.write_fallible(&mut (&data[start..end]).into())?;
+        *offset = end;
+        Ok(end - start)
+    }
+
+    fn write(&self, reader: &mut VmReader) -> Result<usize> {
+        let len = reader.remain();
+        let mut buf = vec![0u8; len];
+        reader.read_fallible(&mut VmWriter::from(buf.as_mut_slice()))?;
+
+        let input = core::str::from_utf8(&buf)
+            .map_err(|_| Error::with_message(Errno::EINVAL, "the input is not valid UTF-8"))?;
+        let (kind, threshold_us, window_us) = parse_trigger(input.trim_end_matches('\0'))
+            .ok_or_else(|| Error::with_message(Errno::EINVAL, "the trigger is invalid"))?;
+
+        let mut trigger = self.trigger.lock();
+        if trigger.is_some() {
+            return_errno_with_message!(Errno::EBUSY, "the file already has a trigger");
+        }
+        *trigger = Some(Trigger::new(
+            self.resource,
+            kind,
+            threshold_us.saturating_mul(1000),
+            window_us.saturating_mul(1000),
+        )?);
+        Ok(len)
+    }
+
+    fn seek(&self, pos: SeekFrom) -> Option<Result<usize>> {
+        let mut offset = self.offset.lock();
+        let new_offset = match pos {
+            SeekFrom::Start(off) => Some(off),
+            SeekFrom::End(off) => pressure_data(self.resource).len().checked_add_signed(off),
+            SeekFrom::Current(off) => offset.checked_add_signed(off),
+        };
+        let Some(new_offset) = new_offset.filter(|off| *off <= isize::MAX as usize) else {
+            return Some(Err(Error::with_message(
+                Errno::EINVAL,
+                "the file offset is invalid",
+            )));
+        };
+        *offset = new_offset;
+        Some(Ok(new_offset))
+    }
+}
+
+/// Parses `<some|full> <threshold> <window>`.
+fn parse_trigger(input: &str) -> Option<(StallKind, u64, u64)> {
+    let mut tokens = input.split_whitespace();
+    let kind = match tokens.next()? {
+        "some" => StallKind::Some,
+        "full" => StallKind::Full,
+        _ => return None,
+    };
+    let threshold = tokens.next()?.parse().ok()?;
+    let window = tokens.next()?.parse().ok()?;
+    if tokens.next().is_some() {
+        return None;
+    }
+    Some((kind, threshold, window))
+}
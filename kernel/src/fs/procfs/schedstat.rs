@@ -0,0 +1,60 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! This module offers `/proc/schedstat` file support, which tells the user space
+//! about the scheduling statistics of each CPU.
+//!
+//! The scheduling domains are not reported, since there is no load balancing between CPUs.
+//!
+//! Reference: <https://www.kernel.org/doc/html/latest/scheduler/sched-stats.html>
+
+use alloc::format;
+
+use ostd::{cpu::all_cpus, timer::Jiffies};
+
+use crate::{
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::Inode,
+    },
+    prelude::*,
+    sched,
+};
+
+/// The version of the format of `/proc/schedstat`.
+const SCHEDSTAT_VERSION: u32 = 15;
+
+/// Represents the inode at `/proc/schedstat`.
+pub struct SchedStatFileOps;
+
+impl SchedStatFileOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self).parent(parent).build().unwrap()
+    }
+}
+
+impl FileOps for SchedStatFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let mut output = format!(
+            "version {}\ntimestamp {}\n",
+            SCHEDSTAT_VERSION,
+            Jiffies::elapsed().as_u64()
+        );
+        for cpu in all_cpus() {
+            let stat = sched::cpu_sched_stat(cpu);
+            // The second field is always zero since Linux 2.6.23.
+            output.push_str(&format!(
+                "cpu{} {} 0 {} {} {} {} {} {} {}\n",
+                cpu.as_usize(),
+                stat.nr_yields,
+                stat.nr_switches,
+                stat.nr_idle_switches,
+                stat.nr_wakeups,
+                stat.nr_local_wakeups,
+                stat.run_time_ns,
+                stat.wait_time_ns,
+                stat.nr_timeslices,
+            ));
+        }
+        Ok(output.into_bytes())
+    }
+}
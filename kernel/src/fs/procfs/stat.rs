@@ -0,0 +1,91 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! This module offers `/proc/stat` file support, which tells the user space
+//! about the kernel and system statistics.
+//!
+//! Reference: <https://www.man7.org/linux/man-pages/man5/proc_stat.5.html>
+
+use alloc::format;
+use core::{fmt::Write, time::Duration};
+
+use ostd::cpu::all_cpus;
+
+use crate::{
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::Inode,
+    },
+    prelude::*,
+    process::posix_thread::{self, cpu_time_stats, CpuTimeStats},
+    sched,
+    time::{clocks::MonotonicClock, duration_to_clock_t, SystemTime, START_TIME},
+};
+
+/// Represents the inode at `/proc/stat`.
+pub struct StatFileOps;
+
+impl StatFileOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self).parent(parent).build().unwrap()
+    }
+}
+
+impl FileOps for StatFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let uptime = MonotonicClock::get().read_time();
+        let cpu_stats: Vec<CpuTimeStats> = all_cpus().map(cpu_time_stats).collect();
+
+        let mut output = String::new();
+        let total_idle = cpu_stats.iter().map(|stats| idle_time(stats, uptime)).sum();
+        let total_stats = cpu_stats
+            .iter()
+            .fold(CpuTimeStats::default(), |total, stats| CpuTimeStats {
+                user: total.user + stats.user,
+                system: total.system + stats.system,
+                irq: total.irq + stats.irq,
+                softirq: total.softirq + stats.softirq,
+                nr_switches: total.nr_switches + stats.nr_switches,
+            });
+        write_cpu_line(&mut output, "cpu", &total_stats, total_idle);
+        for (cpu, stats) in cpu_stats.iter().enumerate() {
+            let name = format!("cpu{}", cpu);
+            write_cpu_line(&mut output, &name, stats, idle_time(stats, uptime));
+        }
+
+        let boot_time = START_TIME
+            .get()
+            .unwrap()
+            .duration_since(&SystemTime::UNIX_EPOCH)?
+            .as_secs();
+        let (_, nr_running) = sched::nr_queued_and_running();
+        writeln!(output, "ctxt {}", total_stats.nr_switches).unwrap();
+        writeln!(output, "btime {}", boot_time).unwrap();
+        // The TIDs are allocated incrementally, so the last TID is the number of the threads
+        // that have been created.
+        writeln!(output, "processes {}", posix_thread::last_tid()).unwrap();
+        writeln!(output, "procs_running {}", nr_running).unwrap();
+
+        Ok(output.into_bytes())
+    }
+}
+
+/// Returns the time that the CPU is not running any threads or handling interrupts.
+fn idle_time(stats: &CpuTimeStats, uptime: Duration) -> Duration {
+    uptime.saturating_sub(stats.user + stats.system + stats.irq + stats.softirq)
+}
+
+fn write_cpu_line(output: &mut String, name: &str, stats: &CpuTimeStats, idle: Duration) {
+    // The fields are `user`, `nice`, `system`, `idle`, `iowait`, `irq`, `softirq`, `steal`,
+    // `guest`, and `guest_nice`.
+    writeln!(
+        output,
+        "{} {} 0 {} {} 0 {} {} 0 0 0",
+        name,
+        duration_to_clock_t(stats.user),
+        duration_to_clock_t(stats.system),
+        duration_to_clock_t(idle),
+        duration_to_clock_t(stats.irq),
+        duration_to_clock_t(stats.softirq),
+    )
+    .unwrap();
+}
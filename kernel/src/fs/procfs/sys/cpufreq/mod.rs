@@ -0,0 +1,65 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! This module offers `/proc/sys/cpufreq`, which works like `/sys/devices/system/cpu/cpufreq`
+//! in Linux.
+//!
+//! Each CPU frequency policy is a directory `policyN`, and the tunables of the ondemand
+//! governor are in the directory `ondemand`.
+
+use alloc::format;
+
+use self::{ondemand::OndemandDirOps, policy::PolicyDirOps};
+use super::read_value;
+use crate::{
+    fs::{
+        procfs::template::{DirOps, ProcDir, ProcDirBuilder},
+        utils::{DirEntryVecExt, Inode},
+    },
+    prelude::*,
+};
+
+mod ondemand;
+mod policy;
+
+/// Represents the inode at `/proc/sys/cpufreq`.
+pub struct CpufreqDirOps;
+
+impl CpufreqDirOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcDirBuilder::new(Self).parent(parent).build().unwrap()
+    }
+}
+
+impl DirOps for CpufreqDirOps {
+    fn lookup_child(&self, this_ptr: Weak<dyn Inode>, name: &str) -> Result<Arc<dyn Inode>> {
+        if name == "ondemand" {
+            return Ok(OndemandDirOps::new_inode(this_ptr));
+        }
+
+        let Some(policy) = name
+            .strip_prefix("policy")
+            .and_then(|index| index.parse::<usize>().ok())
+            .and_then(aster_cpufreq::get_policy)
+        else {
+            return_errno!(Errno::ENOENT);
+        };
+        Ok(PolicyDirOps::new_inode(policy, this_ptr))
+    }
+
+    fn populate_children(&self, this_ptr: Weak<dyn Inode>) {
+        let this = {
+            let this = this_ptr.upgrade().unwrap();
+            this.downcast_ref::<ProcDir<CpufreqDirOps>>()
+                .unwrap()
+                .this()
+        };
+        let mut cached_children = this.cached_children().write();
+        cached_children
+            .put_entry_if_not_found("ondemand", || OndemandDirOps::new_inode(this_ptr.clone()));
+        for policy in aster_cpufreq::all_policies() {
+            cached_children.put_entry_if_not_found(&format!("policy{}", policy.index()), || {
+                PolicyDirOps::new_inode(policy.clone(), this_ptr.clone())
+            });
+        }
+    }
+}
@@ -0,0 +1,117 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::format;
+
+use super::read_value;
+use crate::{
+    fs::{
+        procfs::template::{DirOps, FileOps, ProcDir, ProcDirBuilder, ProcFileBuilder},
+        utils::{DirEntryVecExt, Inode, InodeMode},
+    },
+    prelude::*,
+};
+
+/// Represents the inode at `/proc/sys/cpufreq/ondemand`.
+pub struct OndemandDirOps;
+
+impl OndemandDirOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcDirBuilder::new(Self).parent(parent).build().unwrap()
+    }
+}
+
+impl DirOps for OndemandDirOps {
+    fn lookup_child(&self, this_ptr: Weak<dyn Inode>, name: &str) -> Result<Arc<dyn Inode>> {
+        let Some(tunable) = Tunable::ALL.into_iter().find(|t| t.name() == name) else {
+            return_errno!(Errno::ENOENT);
+        };
+        Ok(TunableFileOps::new_inode(tunable, this_ptr))
+    }
+
+    fn populate_children(&self, this_ptr: Weak<dyn Inode>) {
+        let this = {
+            let this = this_ptr.upgrade().unwrap();
+            this.downcast_ref::<ProcDir<OndemandDirOps>>()
+                .unwrap()
+                .this()
+        };
+        let mut cached_children = this.cached_children().write();
+        for tunable in Tunable::ALL {
+            cached_children.put_entry_if_not_found(tunable.name(), || {
+                TunableFileOps::new_inode(tunable, this_ptr.clone())
+            });
+        }
+    }
+}
+
+/// A tunable of the ondemand governor.
+#[derive(Clone, Copy)]
+enum Tunable {
+    /// The utilization threshold in percent
+    UpThreshold,
+    /// The sampling rate in microseconds, like Linux
+    SamplingRate,
+    /// The minimum sampling rate in microseconds
+    SamplingRateMin,
+}
+
+impl Tunable {
+    const ALL: [Tunable; 3] = [
+        Tunable::UpThreshold,
+        Tunable::SamplingRate,
+        Tunable::SamplingRateMin,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Tunable::UpThreshold => "up_threshold",
+            Tunable::SamplingRate => "sampling_rate",
+            Tunable::SamplingRateMin => "sampling_rate_min",
+        }
+    }
+}
+
+/// Represents the inode at `/proc/sys/cpufreq/ondemand/<tunable>`.
+struct TunableFileOps(Tunable);
+
+impl TunableFileOps {
+    pub fn new_inode(tunable: Tunable, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        let inode = ProcFileBuilder::new(Self(tunable))
+            .parent(parent)
+            .build()
+            .unwrap();
+        if !matches!(tunable, Tunable::SamplingRateMin) {
+            inode
+                .set_mode(InodeMode::from_bits_truncate(0o644))
+                .unwrap();
+        }
+        inode
+    }
+}
+
+impl FileOps for TunableFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let value = match self.0 {
+            Tunable::UpThreshold => aster_cpufreq::up_threshold(),
+            Tunable::SamplingRate => aster_cpufreq::sampling_rate_ms() * 1000,
+            Tunable::SamplingRateMin => aster_cpufreq::MIN_SAMPLING_RATE_MS * 1000,
+        };
+        Ok(format!("{}\n", value).into_bytes())
+    }
+
+    fn write_at(&self, _offset: usize, reader: &mut VmReader) -> Result<usize> {
+        let len = reader.remain();
+        let value: u32 = read_value(reader)?;
+
+        let result = match self.0 {
+            Tunable::UpThreshold => aster_cpufreq::set_up_threshold(value),
+            Tunable::SamplingRate => aster_cpufreq::set_sampling_rate_ms(value / 1000),
+            Tunable::SamplingRateMin => {
+                return_errno_with_message!(Errno::EPERM, "the tunable is read-only")
+            }
+        };
+        result.map_err(|_| Error::with_message(Errno::EINVAL, "the value is out of range"))?;
+
+        Ok(len)
+    }
+}
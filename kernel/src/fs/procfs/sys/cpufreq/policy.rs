@@ -0,0 +1,214 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::format;
+use core::fmt::Write;
+
+use aster_cpufreq::{CpufreqPolicy, Governor};
+
+use super::read_value;
+use crate::{
+    fs::{
+        procfs::template::{DirOps, FileOps, ProcDir, ProcDirBuilder, ProcFileBuilder},
+        utils::{DirEntryVecExt, Inode, InodeMode},
+    },
+    prelude::*,
+};
+
+/// Represents the inode at `/proc/sys/cpufreq/policyN`.
+pub struct PolicyDirOps(Arc<CpufreqPolicy>);
+
+impl PolicyDirOps {
+    pub fn new_inode(policy: Arc<CpufreqPolicy>, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcDirBuilder::new(Self(policy))
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl DirOps for PolicyDirOps {
+    fn lookup_child(&self, this_ptr: Weak<dyn Inode>, name: &str) -> Result<Arc<dyn Inode>> {
+        let Some(attr) = PolicyAttr::ALL.into_iter().find(|a| a.name() == name) else {
+            return_errno!(Errno::ENOENT);
+        };
+        Ok(PolicyAttrFileOps::new_inode(self.0.clone(), attr, this_ptr))
+    }
+
+    fn populate_children(&self, this_ptr: Weak<dyn Inode>) {
+        let this = {
+            let this = this_ptr.upgrade().unwrap();
+            this.downcast_ref::<ProcDir<PolicyDirOps>>().unwrap().this()
+        };
+        let mut cached_children = this.cached_children().write();
+        for attr in PolicyAttr::ALL {
+            cached_children.put_entry_if_not_found(attr.name(), || {
+                PolicyAttrFileOps::new_inode(self.0.clone(), attr, this_ptr.clone())
+            });
+        }
+    }
+}
+
+/// An attribute of a policy, which has the same name and format as Linux.
+#[derive(Clone, Copy)]
+enum PolicyAttr {
+    AffectedCpus,
+    CpuinfoCurFreq,
+    CpuinfoMaxFreq,
+    CpuinfoMinFreq,
+    CpuinfoTransitionLatency,
+    ScalingAvailableFrequencies,
+    ScalingAvailableGovernors,
+    ScalingCurFreq,
+    ScalingDriver,
+    ScalingGovernor,
+    ScalingMaxFreq,
+    ScalingMinFreq,
+    ScalingSetspeed,
+    TotalTrans,
+}
+
+impl PolicyAttr {
+    const ALL: [PolicyAttr; 14] = [
+        PolicyAttr::AffectedCpus,
+        PolicyAttr::CpuinfoCurFreq,
+        PolicyAttr::CpuinfoMaxFreq,
+        PolicyAttr::CpuinfoMinFreq,
+        PolicyAttr::CpuinfoTransitionLatency,
+        PolicyAttr::ScalingAvailableFrequencies,
+        PolicyAttr::ScalingAvailableGovernors,
+        PolicyAttr::ScalingCurFreq,
+        PolicyAttr::ScalingDriver,
+        PolicyAttr::ScalingGovernor,
+        PolicyAttr::ScalingMaxFreq,
+        PolicyAttr::ScalingMinFreq,
+        PolicyAttr::ScalingSetspeed,
+        PolicyAttr::TotalTrans,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            PolicyAttr::AffectedCpus => "affected_cpus",
+            PolicyAttr::CpuinfoCurFreq => "cpuinfo_cur_freq",
+            PolicyAttr::CpuinfoMaxFreq => "cpuinfo_max_freq",
+            PolicyAttr::CpuinfoMinFreq => "cpuinfo_min_freq",
+            PolicyAttr::CpuinfoTransitionLatency => "cpuinfo_transition_latency",
+            PolicyAttr::ScalingAvailableFrequencies => "scaling_available_frequencies",
+            PolicyAttr::ScalingAvailableGovernors => "scaling_available_governors",
+            PolicyAttr::ScalingCurFreq => "scaling_cur_freq",
+            PolicyAttr::ScalingDriver => "scaling_driver",
+            PolicyAttr::ScalingGovernor => "scaling_governor",
+            PolicyAttr::ScalingMaxFreq => "scaling_max_freq",
+            PolicyAttr::ScalingMinFreq => "scaling_min_freq",
+            PolicyAttr::ScalingSetspeed => "scaling_setspeed",
+            PolicyAttr::TotalTrans => "total_trans",
+        }
+    }
+
+    fn is_writable(&self) -> bool {
+        matches!(
+            self,
+            PolicyAttr::ScalingGovernor
+                | PolicyAttr::ScalingMaxFreq
+                | PolicyAttr::ScalingMinFreq
+                | PolicyAttr::ScalingSetspeed
+        )
+    }
+}
+
+/// Represents the inode at `/proc/sys/cpufreq/policyN/<attr>`.
+struct PolicyAttrFileOps {
+    policy: Arc<CpufreqPolicy>,
+    attr: PolicyAttr,
+}
+
+impl PolicyAttrFileOps {
+    pub fn new_inode(
+        policy: Arc<CpufreqPolicy>,
+        attr: PolicyAttr,
+        parent: Weak<dyn Inode>,
+    ) -> Arc<dyn Inode> {
+        let inode = ProcFileBuilder::new(Self { policy, attr })
+            .parent(parent)
+            .build()
+            .unwrap();
+        if attr.is_writable() {
+            inode
+                .set_mode(InodeMode::from_bits_truncate(0o644))
+                .unwrap();
+        }
+        inode
+    }
+}
+
+impl FileOps for PolicyAttrFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let policy = &self.policy;
+        let mut output = match self.attr {
+            PolicyAttr::AffectedCpus => {
+                let mut output = String::new();
+                for cpu in policy.cpus().iter() {
+                    write!(output, "{} ", cpu.as_usize()).unwrap();
+                }
+                output.pop();
+                output
+            }
+            PolicyAttr::CpuinfoCurFreq => match policy.hw_cur_freq() {
+                Some(freq) => format!("{}", freq),
+                None => "<unknown>".to_string(),
+            },
+            PolicyAttr::CpuinfoMaxFreq => format!("{}", policy.cpuinfo_max_freq()),
+            PolicyAttr::CpuinfoMinFreq => format!("{}", policy.cpuinfo_min_freq()),
+            PolicyAttr::CpuinfoTransitionLatency => {
+                format!("{}", policy.transition_latency_ns())
+            }
+            PolicyAttr::ScalingAvailableFrequencies => {
+                let mut output = String::new();
+                for opp in policy.opps() {
+                    write!(output, "{} ", opp.freq_khz).unwrap();
+                }
+                output
+            }
+            PolicyAttr::ScalingAvailableGovernors => {
+                let mut output = String::new();
+                for governor in Governor::ALL {
+                    write!(output, "{} ", governor.name()).unwrap();
+                }
+                output
+            }
+            PolicyAttr::ScalingCurFreq => format!("{}", policy.cur_freq()),
+            PolicyAttr::ScalingDriver => policy.driver_name().to_string(),
+            PolicyAttr::ScalingGovernor => policy.governor().name().to_string(),
+            PolicyAttr::ScalingMaxFreq => format!("{}", policy.max_freq()),
+            PolicyAttr::ScalingMinFreq => format!("{}", policy.min_freq()),
+            PolicyAttr::ScalingSetspeed if policy.governor() == Governor::Userspace => {
+                format!("{}", policy.setspeed())
+            }
+            PolicyAttr::ScalingSetspeed => "<unsupported>".to_string(),
+            PolicyAttr::TotalTrans => format!("{}", policy.nr_transitions()),
+        };
+        output.push('\n');
+        Ok(output.into_bytes())
+    }
+
+    fn write_at(&self, _offset: usize, reader: &mut VmReader) -> Result<usize> {
+        let len = reader.remain();
+        let policy = &self.policy;
+
+        match self.attr {
+            PolicyAttr::ScalingGovernor => {
+                let name: String = read_value(reader)?;
+                let governor = Governor::from_name(&name)
+                    .ok_or_else(|| Error::with_message(Errno::EINVAL, "unknown governor"))?;
+                policy.set_governor(governor);
+            }
+            PolicyAttr::ScalingMaxFreq => policy.set_max_freq(read_value(reader)?),
+            PolicyAttr::ScalingMinFreq => policy.set_min_freq(read_value(reader)?),
+            PolicyAttr::ScalingSetspeed => policy
+                .set_setspeed(read_value(reader)?)
+                .map_err(|_| Error::with_message(Errno::EINVAL, "the governor is not userspace"))?,
+            _ => return_errno_with_message!(Errno::EPERM, "the attribute is read-only"),
+        }
+
+        Ok(len)
+    }
+}
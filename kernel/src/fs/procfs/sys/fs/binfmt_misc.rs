@@ -0,0 +1,218 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use crate::{
+    fs::{
+        procfs::template::{DirOps, FileOps, ProcDir, ProcDirBuilder, ProcFileBuilder},
+        utils::{DirEntryVecExt, Inode, InodeMode},
+    },
+    prelude::*,
+    process::binfmt_misc,
+};
+
+/// Represents the inode at `/proc/sys/fs/binfmt_misc`.
+pub struct BinfmtMiscDirOps;
+
+impl BinfmtMiscDirOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcDirBuilder::new(Self)
+            .parent(parent)
+            // The directory must be volatile, because the handlers can be unregistered.
+            .volatile()
+            .build()
+            .unwrap()
+    }
+}
+
+impl DirOps for BinfmtMiscDirOps {
+    fn lookup_child(&self, this_ptr: Weak<dyn Inode>, name: &str) -> Result<Arc<dyn Inode>> {
+        let inode = match name {
+            "register" => RegisterFileOps::new_inode(this_ptr.clone()),
+            "status" => StatusFileOps::new_inode(this_ptr.clone()),
+            _ => {
+                if binfmt_misc::lookup_entry(name).is_none() {
+                    return_errno!(Errno::ENOENT);
+                }
+                EntryFileOps::new_inode(name.to_string(), this_ptr.clone())
+            }
+        };
+        Ok(inode)
+    }
+
+    fn populate_children(&self, this_ptr: Weak<dyn Inode>) {
+        let this = {
+            let this = this_ptr.upgrade().unwrap();
+            this.downcast_ref::<ProcDir<BinfmtMiscDirOps>>()
+                .unwrap()
+                .this()
+        };
+        let mut cached_children = this.cached_children().write();
+        cached_children
+            .put_entry_if_not_found("register", || RegisterFileOps::new_inode(this_ptr.clone()));
+        cached_children
+            .put_entry_if_not_found("status", || StatusFileOps::new_inode(this_ptr.clone()));
+        for name in binfmt_misc::entry_names() {
+            cached_children.put_entry_if_not_found(&name, || {
+                EntryFileOps::new_inode(name.clone(), this_ptr.clone())
+            });
+        }
+    }
+}
+
+/// Removes the cached inodes of the unregistered handlers from the directory.
+fn remove_stale_entries(dir: &Weak<dyn Inode>) {
+    let Some(dir) = dir.upgrade() else {
+        return;
+    };
+    let dir = dir.downcast_ref::<ProcDir<BinfmtMiscDirOps>>().unwrap();
+
+    let mut cached_children = dir.cached_children().write();
+    let stale_names: Vec<String> = cached_children
+        .iter()
+        .map(|(name, _)| name.clone())
+        .filter(|name| {
+            name != "register" && name != "status" && binfmt_misc::lookup_entry(name).is_none()
+        })
+        .collect();
+    for name in stale_names {
+        cached_children.remove_entry_by_name(&name);
+    }
+}
+
+/// The actions that can be written to the `status` file and the files of the handlers.
+enum Action {
+    Disable,
+    Enable,
+    Remove,
+}
+
+impl Action {
+    fn read_from(reader: &mut VmReader) -> Result<Self> {
+        let len = reader.remain();
+        let mut buf = vec![0u8; len];
+        reader.read_fallible(&mut VmWriter::from(buf.as_mut_slice()))?;
+
+        match core::str::from_utf8(&buf).map(str::trim) {
+            Ok("0") => Ok(Self::Disable),
+            Ok("1") => Ok(Self::Enable),
+            Ok("-1") => Ok(Self::Remove),
+            _ => return_errno_with_message!(Errno::EINVAL, "invalid action"),
+        }
+    }
+}
+
+/// Represents the inode at `/proc/sys/fs/binfmt_misc/register`.
+struct RegisterFileOps;
+
+impl RegisterFileOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        let inode = ProcFileBuilder::new(Self).parent(parent).build().unwrap();
+        inode
+            .set_mode(InodeMode::from_bits_truncate(0o200))
+            .unwrap();
+        inode
+    }
+}
+
+impl FileOps for RegisterFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        return_errno_with_message!(Errno::EINVAL, "the register file cannot be read");
+    }
+
+    fn write_at(&self, _offset: usize, reader: &mut VmReader) -> Result<usize> {
+        let len = reader.remain();
+        let mut buf = vec![0u8; len];
+        reader.read_fallible(&mut VmWriter::from(buf.as_mut_slice()))?;
+
+        binfmt_misc::register(&buf)?;
+        Ok(len)
+    }
+}
+
+/// Represents the inode at `/proc/sys/fs/binfmt_misc/status`.
+struct StatusFileOps {
+    dir: Weak<dyn Inode>,
+}
+
+impl StatusFileOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        let inode = ProcFileBuilder::new(Self {
+            dir: parent.clone(),
+        })
+        .parent(parent)
+        .build()
+        .unwrap();
+        inode
+            .set_mode(InodeMode::from_bits_truncate(0o644))
+            .unwrap();
+        inode
+    }
+}
+
+impl FileOps for StatusFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let output = if binfmt_misc::is_enabled() {
+            "enabled\n"
+        } else {
+            "disabled\n"
+        };
+        Ok(output.as_bytes().to_vec())
+    }
+
+    fn write_at(&self, _offset: usize, reader: &mut VmReader) -> Result<usize> {
+        let len = reader.remain();
+        match Action::read_from(reader)? {
+            Action::Disable => binfmt_misc::set_enabled(false),
+            Action::Enable => binfmt_misc::set_enabled(true),
+            Action::Remove => {
+                binfmt_misc::unregister_all();
+                remove_stale_entries(&self.dir);
+            }
+        }
+        Ok(len)
+    }
+}
+
+/// Represents the inode at `/proc/sys/fs/binfmt_misc/[name]`.
+struct EntryFileOps {
+    name: String,
+    dir: Weak<dyn Inode>,
+}
+
+impl EntryFileOps {
+    pub fn new_inode(name: String, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        let inode = ProcFileBuilder::new(Self {
+            name,
+            dir: parent.clone(),
+        })
+        .parent(parent)
+        .build()
+        .unwrap();
+        inode
+            .set_mode(InodeMode::from_bits_truncate(0o644))
+            .unwrap();
+        inode
+    }
+}
+
+impl FileOps for EntryFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let entry = binfmt_misc::lookup_entry(&self.name)
+            .ok_or_else(|| Error::with_message(Errno::ENOENT, "the handler is unregistered"))?;
+        Ok(entry.status().into_bytes())
+    }
+
+    fn write_at(&self, _offset: usize, reader: &mut VmReader) -> Result<usize> {
+        let len = reader.remain();
+        let entry = binfmt_misc::lookup_entry(&self.name)
+            .ok_or_else(|| Error::with_message(Errno::ENOENT, "the handler is unregistered"))?;
+        match Action::read_from(reader)? {
+            Action::Disable => entry.set_enabled(false),
+            Action::Enable => entry.set_enabled(true),
+            Action::Remove => {
+                binfmt_misc::unregister(entry.name())?;
+                remove_stale_entries(&self.dir);
+            }
+        }
+        Ok(len)
+    }
+}
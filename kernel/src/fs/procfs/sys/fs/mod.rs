@@ -0,0 +1,67 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use self::binfmt_misc::BinfmtMiscDirOps;
+use super::sysctl::{self, Sysctl};
+use crate::{
+    fs::{
+        pipe::{pipe_max_size, set_pipe_max_size},
+        procfs::template::{DirOps, ProcDir, ProcDirBuilder},
+        utils::{DirEntryVecExt, Inode},
+    },
+    prelude::*,
+    process::rlimit::{nr_open, set_nr_open},
+};
+
+mod binfmt_misc;
+
+/// The tunables in `/proc/sys/fs`.
+static SYSCTLS: [Sysctl; 2] = [
+    // The limits are the same as those in Linux, where `nr_open` must be a multiple of the bits
+    // of a word.
+    Sysctl::int(
+        "nr_open",
+        || nr_open() as i64,
+        |value| {
+            set_nr_open(value as u64);
+            Ok(())
+        },
+        64..=0x7fff_ffc0,
+    ),
+    Sysctl::int(
+        "pipe-max-size",
+        || pipe_max_size() as i64,
+        |value| set_pipe_max_size(value as usize),
+        0..=u32::MAX as i64,
+    ),
+];
+
+/// Represents the inode at `/proc/sys/fs`.
+pub struct FsDirOps;
+
+impl FsDirOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcDirBuilder::new(Self).parent(parent).build().unwrap()
+    }
+}
+
+impl DirOps for FsDirOps {
+    fn lookup_child(&self, this_ptr: Weak<dyn Inode>, name: &str) -> Result<Arc<dyn Inode>> {
+        let inode = match name {
+            "binfmt_misc" => BinfmtMiscDirOps::new_inode(this_ptr.clone()),
+            _ => sysctl::lookup(&SYSCTLS, name, this_ptr)?,
+        };
+        Ok(inode)
+    }
+
+    fn populate_children(&self, this_ptr: Weak<dyn Inode>) {
+        let this = {
+            let this = this_ptr.upgrade().unwrap();
+            this.downcast_ref::<ProcDir<FsDirOps>>().unwrap().this()
+        };
+        let mut cached_children = this.cached_children().write();
+        cached_children.put_entry_if_not_found("binfmt_misc", || {
+            BinfmtMiscDirOps::new_inode(this_ptr.clone())
+        });
+        sysctl::populate(&SYSCTLS, &mut cached_children, this_ptr.clone());
+    }
+}
@@ -0,0 +1,80 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::format;
+
+use aster_gpio::GpioChip;
+
+use crate::{
+    fs::{
+        procfs::template::{DirOps, FileOps, ProcDir, ProcDirBuilder, ProcFileBuilder},
+        utils::{DirEntryVecExt, Inode},
+    },
+    prelude::*,
+};
+
+/// Represents the inode at `/proc/sys/gpio/gpiochipN`.
+pub struct ChipDirOps(Arc<GpioChip>);
+
+impl ChipDirOps {
+    pub fn new_inode(chip: Arc<GpioChip>, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcDirBuilder::new(Self(chip))
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+const ATTRS: [&str; 3] = ["base", "label", "ngpio"];
+
+impl DirOps for ChipDirOps {
+    fn lookup_child(&self, this_ptr: Weak<dyn Inode>, name: &str) -> Result<Arc<dyn Inode>> {
+        let Some(attr) = ATTRS.into_iter().find(|attr| *attr == name) else {
+            return_errno!(Errno::ENOENT);
+        };
+        Ok(ChipAttrFileOps::new_inode(self.0.clone(), attr, this_ptr))
+    }
+
+    fn populate_children(&self, this_ptr: Weak<dyn Inode>) {
+        let this = {
+            let this = this_ptr.upgrade().unwrap();
+            this.downcast_ref::<ProcDir<ChipDirOps>>().unwrap().this()
+        };
+        let mut cached_children = this.cached_children().write();
+        for attr in ATTRS {
+            cached_children.put_entry_if_not_found(attr, || {
+                ChipAttrFileOps::new_inode(self.0.clone(), attr, this_ptr.clone())
+            });
+        }
+    }
+}
+
+/// Represents the inode at `/proc/sys/gpio/gpiochipN/<attr>`.
+struct ChipAttrFileOps {
+    chip: Arc<GpioChip>,
+    attr: &'static str,
+}
+
+impl ChipAttrFileOps {
+    pub fn new_inode(
+        chip: Arc<GpioChip>,
+        attr: &'static str,
+        parent: Weak<dyn Inode>,
+    ) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self { chip, attr })
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for ChipAttrFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let output = match self.attr {
+            "base" => format!("{}\n", self.chip.base()),
+            "label" => format!("{}\n", self.chip.label()),
+            "ngpio" => format!("{}\n", self.chip.ngpio()),
+            _ => unreachable!(),
+        };
+        Ok(output.into_bytes())
+    }
+}
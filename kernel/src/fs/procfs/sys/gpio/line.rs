@@ -0,0 +1,117 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::format;
+
+use aster_gpio::{GpioDesc, GpioDirection};
+
+use super::{gpio_error, read_value};
+use crate::{
+    fs::{
+        procfs::template::{DirOps, FileOps, ProcDir, ProcDirBuilder, ProcFileBuilder},
+        utils::{DirEntryVecExt, Inode, InodeMode},
+    },
+    prelude::*,
+};
+
+/// Represents the inode at `/proc/sys/gpio/gpioN`.
+pub struct LineDirOps(Arc<GpioDesc>);
+
+impl LineDirOps {
+    pub fn new_inode(desc: Arc<GpioDesc>, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcDirBuilder::new(Self(desc))
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+const ATTRS: [&str; 3] = ["active_low", "direction", "value"];
+
+impl DirOps for LineDirOps {
+    fn lookup_child(&self, this_ptr: Weak<dyn Inode>, name: &str) -> Result<Arc<dyn Inode>> {
+        let Some(attr) = ATTRS.into_iter().find(|attr| *attr == name) else {
+            return_errno!(Errno::ENOENT);
+        };
+        Ok(LineAttrFileOps::new_inode(self.0.clone(), attr, this_ptr))
+    }
+
+    fn populate_children(&self, this_ptr: Weak<dyn Inode>) {
+        let this = {
+            let this = this_ptr.upgrade().unwrap();
+            this.downcast_ref::<ProcDir<LineDirOps>>().unwrap().this()
+        };
+        let mut cached_children = this.cached_children().write();
+        for attr in ATTRS {
+            cached_children.put_entry_if_not_found(attr, || {
+                LineAttrFileOps::new_inode(self.0.clone(), attr, this_ptr.clone())
+            });
+        }
+    }
+}
+
+/// Represents the inode at `/proc/sys/gpio/gpioN/<attr>`.
+struct LineAttrFileOps {
+    desc: Arc<GpioDesc>,
+    attr: &'static str,
+}
+
+impl LineAttrFileOps {
+    pub fn new_inode(
+        desc: Arc<GpioDesc>,
+        attr: &'static str,
+        parent: Weak<dyn Inode>,
+    ) -> Arc<dyn Inode> {
+        let inode = ProcFileBuilder::new(Self { desc, attr })
+            .parent(parent)
+            .build()
+            .unwrap();
+        inode
+            .set_mode(InodeMode::from_bits_truncate(0o644))
+            .unwrap();
+        inode
+    }
+}
+
+impl FileOps for LineAttrFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let output = match self.attr {
+            "active_low" => format!("{}\n", self.desc.is_active_low() as u8),
+            "direction" => match self.desc.direction() {
+                GpioDirection::Input => "in\n".to_string(),
+                GpioDirection::Output => "out\n".to_string(),
+            },
+            "value" => format!("{}\n", self.desc.get_value() as u8),
+            _ => unreachable!(),
+        };
+        Ok(output.into_bytes())
+    }
+
+    fn write_at(&self, _offset: usize, reader: &mut VmReader) -> Result<usize> {
+        let len = reader.remain();
+        let desc = &self.desc;
+
+        match self.attr {
+            "active_low" => desc.set_active_low(read_value::<u32>(reader)? != 0),
+            "direction" => {
+                let direction: String = read_value(reader)?;
+                // Like Linux, `high` and `low` make the line an output with the value.
+                let result = match direction.as_str() {
+                    "in" => desc.direction_input(),
+                    "out" | "low" => desc.direction_output(false),
+                    "high" => desc.direction_output(true),
+                    _ => return_errno_with_message!(Errno::EINVAL, "invalid direction"),
+                };
+                result.map_err(gpio_error)?;
+            }
+            "value" => {
+                if desc.direction() != GpioDirection::Output {
+                    return_errno_with_message!(Errno::EPERM, "the GPIO is not an output");
+                }
+                desc.set_value(read_value::<u32>(reader)? != 0);
+            }
+            _ => unreachable!(),
+        }
+
+        Ok(len)
+    }
+}
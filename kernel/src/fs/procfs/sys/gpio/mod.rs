@@ -0,0 +1,161 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! This module offers `/proc/sys/gpio`, which works like the legacy `/sys/class/gpio` in
+//! Linux.
+//!
+//! Each GPIO chip is a directory `gpiochipN`, where `N` is the number of its first line. A
+//! line is requested by writing its number to `export`, after which it is a directory
+//! `gpioN`, and released by writing its number to `unexport`.
+
+use alloc::{collections::BTreeMap, format};
+
+use aster_gpio::{GpioDesc, GpioError};
+
+use self::{chip::ChipDirOps, line::LineDirOps};
+use super::read_value;
+use crate::{
+    fs::{
+        procfs::template::{DirOps, FileOps, ProcDir, ProcDirBuilder, ProcFileBuilder},
+        utils::{DirEntryVecExt, Inode, InodeMode},
+    },
+    prelude::*,
+};
+
+mod chip;
+mod line;
+
+/// The lines that are exported to the user space, indexed by their numbers.
+static EXPORTED_LINES: Mutex<BTreeMap<u32, Arc<GpioDesc>>> = Mutex::new(BTreeMap::new());
+
+/// Represents the inode at `/proc/sys/gpio`.
+pub struct GpioDirOps;
+
+impl GpioDirOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcDirBuilder::new(Self).parent(parent).build().unwrap()
+    }
+}
+
+impl DirOps for GpioDirOps {
+    fn lookup_child(&self, this_ptr: Weak<dyn Inode>, name: &str) -> Result<Arc<dyn Inode>> {
+        match name {
+            "export" => return Ok(ExportFileOps::new_inode(true, this_ptr)),
+            "unexport" => return Ok(ExportFileOps::new_inode(false, this_ptr)),
+            _ => {}
+        }
+
+        if let Some(chip) = name
+            .strip_prefix("gpiochip")
+            .and_then(|base| base.parse::<u32>().ok())
+            .and_then(|base| {
+                aster_gpio::all_chips()
+                    .into_iter()
+                    .find(|chip| chip.base() == base)
+            })
+        {
+            return Ok(ChipDirOps::new_inode(chip, this_ptr));
+        }
+
+        let Some(desc) = name
+            .strip_prefix("gpio")
+            .and_then(|number| number.parse::<u32>().ok())
+            .and_then(|number| EXPORTED_LINES.lock().get(&number).cloned())
+        else {
+            return_errno!(Errno::ENOENT);
+        };
+        Ok(LineDirOps::new_inode(desc, this_ptr))
+    }
+
+    fn populate_children(&self, this_ptr: Weak<dyn Inode>) {
+        let this = {
+            let this = this_ptr.upgrade().unwrap();
+            this.downcast_ref::<ProcDir<GpioDirOps>>().unwrap().this()
+        };
+        let mut cached_children = this.cached_children().write();
+        cached_children.put_entry_if_not_found("export", || {
+            ExportFileOps::new_inode(true, this_ptr.clone())
+        });
+        cached_children.put_entry_if_not_found("unexport", || {
+            ExportFileOps::new_inode(false, this_ptr.clone())
+        });
+        for chip in aster_gpio::all_chips() {
+            cached_children.put_entry_if_not_found(&format!("gpiochip{}", chip.base()), || {
+                ChipDirOps::new_inode(chip.clone(), this_ptr.clone())
+            });
+        }
+
+        // The unexported lines are removed from the cache.
+        let exported_lines = EXPORTED_LINES.lock();
+        let unexported: Vec<String> = cached_children
+            .iter()
+            .map(|(name, _)| name)
+            .filter(|name| {
+                name.strip_prefix("gpio")
+                    .and_then(|number| number.parse::<u32>().ok())
+                    .is_some_and(|number| !exported_lines.contains_key(&number))
+            })
+            .cloned()
+            .collect();
+        for name in unexported {
+            cached_children.remove_entry_by_name(&name);
+        }
+        for (number, desc) in exported_lines.iter() {
+            cached_children.put_entry_if_not_found(&format!("gpio{}", number), || {
+                LineDirOps::new_inode(desc.clone(), this_ptr.clone())
+            });
+        }
+    }
+}
+
+/// Represents the inode at `/proc/sys/gpio/export` or `/proc/sys/gpio/unexport`.
+struct ExportFileOps {
+    is_export: bool,
+}
+
+impl ExportFileOps {
+    pub fn new_inode(is_export: bool, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        let inode = ProcFileBuilder::new(Self { is_export })
+            .parent(parent)
+            .build()
+            .unwrap();
+        inode
+            .set_mode(InodeMode::from_bits_truncate(0o200))
+            .unwrap();
+        inode
+    }
+}
+
+impl FileOps for ExportFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        return_errno_with_message!(Errno::EPERM, "the file is write-only")
+    }
+
+    fn write_at(&self, _offset: usize, reader: &mut VmReader) -> Result<usize> {
+        let len = reader.remain();
+        let number: u32 = read_value(reader)?;
+
+        let mut exported_lines = EXPORTED_LINES.lock();
+        if self.is_export {
+            if exported_lines.contains_key(&number) {
+                return_errno_with_message!(Errno::EBUSY, "the GPIO has been exported");
+            }
+            let desc = aster_gpio::request_gpio(number, "sysfs").map_err(gpio_error)?;
+            exported_lines.insert(number, Arc::new(desc));
+        } else if exported_lines.remove(&number).is_none() {
+            return_errno_with_message!(Errno::EINVAL, "the GPIO is not exported");
+        }
+
+        Ok(len)
+    }
+}
+
+fn gpio_error(err: GpioError) -> Error {
+    match err {
+        GpioError::InvalidArgs => Error::with_message(Errno::EINVAL, "invalid GPIO"),
+        GpioError::Busy => Error::with_message(Errno::EBUSY, "the GPIO is busy"),
+        GpioError::NotSupported => {
+            Error::with_message(Errno::EOPNOTSUPP, "the GPIO does not support the operation")
+        }
+        GpioError::IoError => Error::with_message(Errno::EIO, "the GPIO reports an error"),
+    }
+}
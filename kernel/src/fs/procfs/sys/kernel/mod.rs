@@ -1,18 +1,70 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use alloc::format;
+
+use aster_logger::{CONSOLE_LOGLEVEL_DEFAULT, CONSOLE_LOGLEVEL_MIN, MESSAGE_LOGLEVEL_DEFAULT};
+
+use super::sysctl::{self, Sysctl};
 use crate::{
     fs::{
         procfs::{
-            sys::kernel::cap_last_cap::CapLastCapFileOps,
+            sys::kernel::seccomp::SeccompDirOps,
             template::{DirOps, ProcDirBuilder},
             ProcDir,
         },
         utils::{DirEntryVecExt, Inode},
     },
     prelude::*,
+    process::{
+        credentials::capabilities::CapSet, randomize_va_space, set_randomize_va_space,
+        RandomizeVaSpace,
+    },
+    thread::oops,
 };
 
-mod cap_last_cap;
+mod seccomp;
+
+/// The tunables in `/proc/sys/kernel`.
+static SYSCTLS: [Sysctl; 4] = [
+    Sysctl::int_ro("cap_last_cap", || CapSet::most_significant_bit() as i64),
+    Sysctl::bool(
+        "panic_on_oops",
+        oops::panic_on_oops,
+        oops::set_panic_on_oops,
+    ),
+    // The current, default message, minimum and default console log levels, of which only the
+    // current console log level can be changed.
+    Sysctl::string("printk", printk, set_printk),
+    Sysctl::int(
+        "randomize_va_space",
+        || randomize_va_space() as i64,
+        |level| {
+            set_randomize_va_space(RandomizeVaSpace::try_from(level as u8)?);
+            Ok(())
+        },
+        0..=RandomizeVaSpace::Full as i64,
+    ),
+];
+
+fn printk() -> String {
+    format!(
+        "{}\t{}\t{}\t{}",
+        aster_logger::console_loglevel(),
+        MESSAGE_LOGLEVEL_DEFAULT,
+        CONSOLE_LOGLEVEL_MIN,
+        CONSOLE_LOGLEVEL_DEFAULT
+    )
+}
+
+fn set_printk(value: &str) -> Result<()> {
+    let level = value
+        .split_whitespace()
+        .next()
+        .and_then(|value| value.parse::<u8>().ok())
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid console log level"))?;
+    aster_logger::set_console_loglevel(level);
+    Ok(())
+}
 
 /// Represents the inode at `/proc/sys/kernel`.
 pub struct KernelDirOps;
@@ -26,8 +78,8 @@ impl KernelDirOps {
 impl DirOps for KernelDirOps {
     fn lookup_child(&self, this_ptr: Weak<dyn Inode>, name: &str) -> Result<Arc<dyn Inode>> {
         let inode = match name {
-            "cap_last_cap" => CapLastCapFileOps::new_inode(this_ptr.clone()),
-            _ => return_errno!(Errno::ENOENT),
+            "seccomp" => SeccompDirOps::new_inode(this_ptr.clone()),
+            _ => sysctl::lookup(&SYSCTLS, name, this_ptr)?,
         };
         Ok(inode)
     }
@@ -38,8 +90,8 @@ impl DirOps for KernelDirOps {
             this.downcast_ref::<ProcDir<KernelDirOps>>().unwrap().this()
         };
         let mut cached_children = this.cached_children().write();
-        cached_children.put_entry_if_not_found("cap_last_cap", || {
-            CapLastCapFileOps::new_inode(this_ptr.clone())
-        });
+        sysctl::populate(&SYSCTLS, &mut cached_children, this_ptr.clone());
+        cached_children
+            .put_entry_if_not_found("seccomp", || SeccompDirOps::new_inode(this_ptr.clone()));
     }
 }
@@ -0,0 +1,31 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::format;
+
+use crate::{
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::Inode,
+    },
+    prelude::*,
+    security::seccomp::available_actions,
+};
+
+/// Represents the inode at `/proc/sys/kernel/seccomp/actions_avail`.
+///
+/// The file contains the names of the seccomp actions that are supported.
+pub struct ActionsAvailFileOps;
+
+impl ActionsAvailFileOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self).parent(parent).build().unwrap()
+    }
+}
+
+impl FileOps for ActionsAvailFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let actions: Vec<&str> = available_actions().collect();
+        let output = format!("{}\n", actions.join(" "));
+        Ok(output.into_bytes())
+    }
+}
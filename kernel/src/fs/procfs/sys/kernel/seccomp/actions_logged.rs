@@ -0,0 +1,48 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::format;
+
+use crate::{
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::{Inode, InodeMode},
+    },
+    prelude::*,
+    security::seccomp::{logged_actions, set_logged_actions},
+};
+
+/// Represents the inode at `/proc/sys/kernel/seccomp/actions_logged`.
+///
+/// The file contains the names of the seccomp actions that can be audited in the kernel log.
+/// Writing the names separated by whitespaces replaces them.
+pub struct ActionsLoggedFileOps;
+
+impl ActionsLoggedFileOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        let inode = ProcFileBuilder::new(Self).parent(parent).build().unwrap();
+        inode
+            .set_mode(InodeMode::from_bits_truncate(0o644))
+            .unwrap();
+        inode
+    }
+}
+
+impl FileOps for ActionsLoggedFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let actions: Vec<&str> = logged_actions().collect();
+        let output = format!("{}\n", actions.join(" "));
+        Ok(output.into_bytes())
+    }
+
+    fn write_at(&self, _offset: usize, reader: &mut VmReader) -> Result<usize> {
+        let len = reader.remain();
+        let mut buf = vec![0u8; len];
+        reader.read_fallible(&mut VmWriter::from(buf.as_mut_slice()))?;
+
+        let names = core::str::from_utf8(&buf)
+            .map_err(|_| Error::with_message(Errno::EINVAL, "invalid seccomp actions"))?;
+        set_logged_actions(names)?;
+
+        Ok(len)
+    }
+}
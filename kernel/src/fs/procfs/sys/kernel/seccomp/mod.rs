@@ -0,0 +1,54 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use crate::{
+    fs::{
+        procfs::{
+            sys::kernel::seccomp::{
+                actions_avail::ActionsAvailFileOps, actions_logged::ActionsLoggedFileOps,
+            },
+            template::{DirOps, ProcDirBuilder},
+            ProcDir,
+        },
+        utils::{DirEntryVecExt, Inode},
+    },
+    prelude::*,
+};
+
+mod actions_avail;
+mod actions_logged;
+
+/// Represents the inode at `/proc/sys/kernel/seccomp`.
+pub struct SeccompDirOps;
+
+impl SeccompDirOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcDirBuilder::new(Self).parent(parent).build().unwrap()
+    }
+}
+
+impl DirOps for SeccompDirOps {
+    fn lookup_child(&self, this_ptr: Weak<dyn Inode>, name: &str) -> Result<Arc<dyn Inode>> {
+        let inode = match name {
+            "actions_avail" => ActionsAvailFileOps::new_inode(this_ptr.clone()),
+            "actions_logged" => ActionsLoggedFileOps::new_inode(this_ptr.clone()),
+            _ => return_errno!(Errno::ENOENT),
+        };
+        Ok(inode)
+    }
+
+    fn populate_children(&self, this_ptr: Weak<dyn Inode>) {
+        let this = {
+            let this = this_ptr.upgrade().unwrap();
+            this.downcast_ref::<ProcDir<SeccompDirOps>>()
+                .unwrap()
+                .this()
+        };
+        let mut cached_children = this.cached_children().write();
+        cached_children.put_entry_if_not_found("actions_avail", || {
+            ActionsAvailFileOps::new_inode(this_ptr.clone())
+        });
+        cached_children.put_entry_if_not_found("actions_logged", || {
+            ActionsLoggedFileOps::new_inode(this_ptr.clone())
+        });
+    }
+}
@@ -1,6 +1,9 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use self::kernel::KernelDirOps;
+use self::{
+    cpufreq::CpufreqDirOps, fs::FsDirOps, gpio::GpioDirOps, kernel::KernelDirOps, net::NetDirOps,
+    power::PowerDirOps, thermal::ThermalDirOps, vm::VmDirOps,
+};
 use crate::{
     fs::{
         procfs::template::{DirOps, ProcDir, ProcDirBuilder},
@@ -9,7 +12,15 @@ use crate::{
     prelude::*,
 };
 
+mod cpufreq;
+mod fs;
+mod gpio;
 mod kernel;
+mod net;
+mod power;
+mod sysctl;
+mod thermal;
+mod vm;
 
 /// Represents the inode at `/proc/sys`.
 pub struct SysDirOps;
@@ -23,7 +34,14 @@ impl SysDirOps {
 impl DirOps for SysDirOps {
     fn lookup_child(&self, this_ptr: Weak<dyn Inode>, name: &str) -> Result<Arc<dyn Inode>> {
         let inode = match name {
+            "cpufreq" => CpufreqDirOps::new_inode(this_ptr.clone()),
+            "fs" => FsDirOps::new_inode(this_ptr.clone()),
+            "gpio" => GpioDirOps::new_inode(this_ptr.clone()),
             "kernel" => KernelDirOps::new_inode(this_ptr.clone()),
+            "net" => NetDirOps::new_inode(this_ptr.clone()),
+            "power" => PowerDirOps::new_inode(this_ptr.clone()),
+            "thermal" => ThermalDirOps::new_inode(this_ptr.clone()),
+            "vm" => VmDirOps::new_inode(this_ptr.clone()),
             _ => return_errno!(Errno::ENOENT),
         };
         Ok(inode)
@@ -36,6 +54,27 @@ impl DirOps for SysDirOps {
         };
         let mut cached_children = this.cached_children().write();
         cached_children
-            .put_entry_if_not_found("kernel", || KernelDirOps::new_inode(this_ptr.clone()))
+            .put_entry_if_not_found("cpufreq", || CpufreqDirOps::new_inode(this_ptr.clone()));
+        cached_children.put_entry_if_not_found("fs", || FsDirOps::new_inode(this_ptr.clone()));
+        cached_children.put_entry_if_not_found("gpio", || GpioDirOps::new_inode(this_ptr.clone()));
+        cached_children
+            .put_entry_if_not_found("kernel", || KernelDirOps::new_inode(this_ptr.clone()));
+        cached_children.put_entry_if_not_found("net", || NetDirOps::new_inode(this_ptr.clone()));
+        cached_children
+            .put_entry_if_not_found("power", || PowerDirOps::new_inode(this_ptr.clone()));
+        cached_children
+            .put_entry_if_not_found("thermal", || ThermalDirOps::new_inode(this_ptr.clone()));
+        cached_children.put_entry_if_not_found("vm", || VmDirOps::new_inode(this_ptr.clone()));
     }
 }
+
+/// Reads the value written to a file.
+fn read_value<T: core::str::FromStr>(reader: &mut VmReader) -> Result<T> {
+    let mut buf = vec![0u8; reader.remain()];
+    reader.read_fallible(&mut VmWriter::from(buf.as_mut_slice()))?;
+
+    core::str::from_utf8(&buf)
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid value"))
+}
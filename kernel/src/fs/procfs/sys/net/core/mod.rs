@@ -0,0 +1,79 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::{
+    fs::{
+        procfs::{
+            sys::sysctl::{self, Sysctl},
+            template::{DirOps, ProcDirBuilder},
+            ProcDir,
+        },
+        utils::Inode,
+    },
+    net::socket::{
+        DEFAULT_RECVBUF, DEFAULT_SENDBUF, MAX_RECVBUF, MAX_SENDBUF, MIN_RECVBUF, MIN_SENDBUF,
+    },
+    prelude::*,
+};
+
+/// The tunables in `/proc/sys/net/core`.
+static SYSCTLS: [Sysctl; 4] = [
+    Sysctl::int(
+        "rmem_default",
+        || load(&DEFAULT_RECVBUF),
+        |value| store(&DEFAULT_RECVBUF, value),
+        MIN_RECVBUF as i64..=i32::MAX as i64,
+    ),
+    Sysctl::int(
+        "rmem_max",
+        || load(&MAX_RECVBUF),
+        |value| store(&MAX_RECVBUF, value),
+        MIN_RECVBUF as i64..=i32::MAX as i64,
+    ),
+    Sysctl::int(
+        "wmem_default",
+        || load(&DEFAULT_SENDBUF),
+        |value| store(&DEFAULT_SENDBUF, value),
+        MIN_SENDBUF as i64..=i32::MAX as i64,
+    ),
+    Sysctl::int(
+        "wmem_max",
+        || load(&MAX_SENDBUF),
+        |value| store(&MAX_SENDBUF, value),
+        MIN_SENDBUF as i64..=i32::MAX as i64,
+    ),
+];
+
+fn load(size: &AtomicU32) -> i64 {
+    size.load(Ordering::Relaxed) as i64
+}
+
+fn store(size: &AtomicU32, value: i64) -> Result<()> {
+    size.store(value as u32, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Represents the inode at `/proc/sys/net/core`.
+pub struct CoreDirOps;
+
+impl CoreDirOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcDirBuilder::new(Self).parent(parent).build().unwrap()
+    }
+}
+
+impl DirOps for CoreDirOps {
+    fn lookup_child(&self, this_ptr: Weak<dyn Inode>, name: &str) -> Result<Arc<dyn Inode>> {
+        sysctl::lookup(&SYSCTLS, name, this_ptr)
+    }
+
+    fn populate_children(&self, this_ptr: Weak<dyn Inode>) {
+        let this = {
+            let this = this_ptr.upgrade().unwrap();
+            this.downcast_ref::<ProcDir<CoreDirOps>>().unwrap().this()
+        };
+        let mut cached_children = this.cached_children().write();
+        sysctl::populate(&SYSCTLS, &mut cached_children, this_ptr);
+    }
+}
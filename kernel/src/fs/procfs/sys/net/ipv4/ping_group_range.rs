@@ -7,23 +7,22 @@ use crate::{
         procfs::template::{FileOps, ProcFileBuilder},
         utils::Inode,
     },
+    net::socket::ip::raw::PING_GROUP_RANGE,
     prelude::*,
-    process::credentials::capabilities::CapSet,
 };
 
-/// Represents the inode at `/proc/sys/kernel/cap_last_cap`.
-pub struct CapLastCapFileOps;
+/// Represents the inode at `/proc/sys/net/ipv4/ping_group_range`.
+pub struct PingGroupRangeFileOps;
 
-impl CapLastCapFileOps {
+impl PingGroupRangeFileOps {
     pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
         ProcFileBuilder::new(Self).parent(parent).build().unwrap()
     }
 }
 
-impl FileOps for CapLastCapFileOps {
+impl FileOps for PingGroupRangeFileOps {
     fn data(&self) -> Result<Vec<u8>> {
-        let cap_last_cap_value = CapSet::most_significant_bit();
-        let output = format!("{}\n", cap_last_cap_value);
+        let output = format!("{}\t{}\n", PING_GROUP_RANGE.start(), PING_GROUP_RANGE.end());
         Ok(output.into_bytes())
     }
 }
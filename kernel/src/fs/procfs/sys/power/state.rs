@@ -0,0 +1,52 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! This module offers `/proc/sys/power/state`, which works like `/sys/power/state` in Linux.
+//!
+//! Reading the file lists the supported sleep states, and writing a state to the file puts
+//! the system into the state. Only "mem" (i.e., suspend-to-RAM) is supported.
+
+use crate::{
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::{Inode, InodeMode},
+    },
+    power,
+    prelude::*,
+};
+
+/// Represents the inode at `/proc/sys/power/state`.
+pub struct StateFileOps;
+
+impl StateFileOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        let inode = ProcFileBuilder::new(Self).parent(parent).build().unwrap();
+        inode
+            .set_mode(InodeMode::from_bits_truncate(0o644))
+            .unwrap();
+        inode
+    }
+}
+
+impl FileOps for StateFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let output = if power::is_suspend_supported() {
+            "mem\n"
+        } else {
+            "\n"
+        };
+        Ok(output.as_bytes().to_vec())
+    }
+
+    fn write_at(&self, _offset: usize, reader: &mut VmReader) -> Result<usize> {
+        let len = reader.remain();
+        let mut buf = vec![0u8; len];
+        reader.read_fallible(&mut VmWriter::from(buf.as_mut_slice()))?;
+
+        match core::str::from_utf8(&buf).map(str::trim) {
+            Ok("mem") => power::suspend_to_ram()?,
+            _ => return_errno_with_message!(Errno::EINVAL, "invalid sleep state"),
+        }
+
+        Ok(len)
+    }
+}
@@ -0,0 +1,229 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The tables of the tunables in `/proc/sys`.
+//!
+//! Like the `ctl_table` in Linux, a directory in `/proc/sys` declares its tunables as a table of
+//! [`Sysctl`], each of which connects a file to the getter and the setter of a tunable. The
+//! files format, parse and check the values in the same way, so that the tunables can be changed
+//! at runtime (e.g., by `sysctl -w` or by the init scripts).
+
+use alloc::format;
+use core::ops::RangeInclusive;
+
+use aster_util::slot_vec::SlotVec;
+
+use super::read_value;
+use crate::{
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::{DirEntryVecExt, Inode, InodeMode},
+    },
+    prelude::*,
+    process::{credentials::capabilities::CapSet, posix_thread::AsPosixThread},
+};
+
+/// A tunable in `/proc/sys`.
+pub(super) struct Sysctl {
+    name: &'static str,
+    handler: Handler,
+    /// The capabilities that are required to change the tunable, besides the file mode
+    write_caps: CapSet,
+}
+
+enum Handler {
+    Int {
+        get: fn() -> i64,
+        set: Option<fn(i64) -> Result<()>>,
+        range: RangeInclusive<i64>,
+    },
+    Bool {
+        get: fn() -> bool,
+        set: Option<fn(bool)>,
+    },
+    String {
+        get: fn() -> String,
+        set: Option<fn(&str) -> Result<()>>,
+    },
+}
+
+impl Sysctl {
+    /// Creates an integer tunable, which can be set to the values in the range.
+    pub(super) const fn int(
+        name: &'static str,
+        get: fn() -> i64,
+        set: fn(i64) -> Result<()>,
+        range: RangeInclusive<i64>,
+    ) -> Self {
+        Self::new(
+            name,
+            Handler::Int {
+                get,
+                set: Some(set),
+                range,
+            },
+        )
+    }
+
+    /// Creates a read-only integer.
+    pub(super) const fn int_ro(name: &'static str, get: fn() -> i64) -> Self {
+        Self::new(
+            name,
+            Handler::Int {
+                get,
+                set: None,
+                range: i64::MIN..=i64::MAX,
+            },
+        )
+    }
+
+    /// Creates a boolean tunable, which is read and written as `0` or `1`.
+    pub(super) const fn bool(name: &'static str, get: fn() -> bool, set: fn(bool)) -> Self {
+        Self::new(
+            name,
+            Handler::Bool {
+                get,
+                set: Some(set),
+            },
+        )
+    }
+
+    /// Creates a string tunable, whose setter parses and checks the written string.
+    ///
+    /// The whitespace around the written string is trimmed.
+    pub(super) const fn string(
+        name: &'static str,
+        get: fn() -> String,
+        set: fn(&str) -> Result<()>,
+    ) -> Self {
+        Self::new(
+            name,
+            Handler::String {
+                get,
+                set: Some(set),
+            },
+        )
+    }
+
+    /// Requires the capabilities to change the tunable.
+    pub(super) const fn write_caps(self, write_caps: CapSet) -> Self {
+        Self { write_caps, ..self }
+    }
+
+    const fn new(name: &'static str, handler: Handler) -> Self {
+        Self {
+            name,
+            handler,
+            write_caps: CapSet::empty(),
+        }
+    }
+
+    fn is_writable(&self) -> bool {
+        match &self.handler {
+            Handler::Int { set, .. } => set.is_some(),
+            Handler::Bool { set, .. } => set.is_some(),
+            Handler::String { set, .. } => set.is_some(),
+        }
+    }
+}
+
+/// Looks up the file of a tunable in the table.
+pub(super) fn lookup(
+    table: &'static [Sysctl],
+    name: &str,
+    parent: Weak<dyn Inode>,
+) -> Result<Arc<dyn Inode>> {
+    let Some(sysctl) = table.iter().find(|sysctl| sysctl.name == name) else {
+        return_errno!(Errno::ENOENT);
+    };
+    Ok(SysctlFileOps::new_inode(sysctl, parent))
+}
+
+/// Puts the files of the tunables in the table into the children of a directory.
+pub(super) fn populate(
+    table: &'static [Sysctl],
+    cached_children: &mut SlotVec<(String, Arc<dyn Inode>)>,
+    parent: Weak<dyn Inode>,
+) {
+    for sysctl in table {
+        cached_children.put_entry_if_not_found(sysctl.name, || {
+            SysctlFileOps::new_inode(sysctl, parent.clone())
+        });
+    }
+}
+
+/// Represents the inode of a tunable in `/proc/sys`.
+struct SysctlFileOps(&'static Sysctl);
+
+impl SysctlFileOps {
+    fn new_inode(sysctl: &'static Sysctl, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        let inode = ProcFileBuilder::new(Self(sysctl))
+            .parent(parent)
+            .build()
+            .unwrap();
+        if sysctl.is_writable() {
+            inode
+                .set_mode(InodeMode::from_bits_truncate(0o644))
+                .unwrap();
+        }
+        inode
+    }
+}
+
+impl FileOps for SysctlFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let output = match &self.0.handler {
+            Handler::Int { get, .. } => format!("{}\n", get()),
+            Handler::Bool { get, .. } => format!("{}\n", get() as u8),
+            Handler::String { get, .. } => format!("{}\n", get()),
+        };
+        Ok(output.into_bytes())
+    }
+
+    fn write_at(&self, _offset: usize, reader: &mut VmReader) -> Result<usize> {
+        if !self.0.is_writable() {
+            return_errno_with_message!(Errno::EPERM, "the tunable is read-only");
+        }
+
+        let write_caps = self.0.write_caps;
+        if !write_caps.is_empty()
+            && !current_thread!()
+                .as_posix_thread()
+                .unwrap()
+                .credentials()
+                .effective_capset()
+                .contains(write_caps)
+        {
+            return_errno_with_message!(Errno::EPERM, "the capabilities are required");
+        }
+
+        let len = reader.remain();
+        match &self.0.handler {
+            Handler::Int {
+                set: Some(set),
+                range,
+                ..
+            } => {
+                let value: i64 = read_value(reader)?;
+                if !range.contains(&value) {
+                    return_errno_with_message!(Errno::EINVAL, "the value is out of range");
+                }
+                set(value)?;
+            }
+            Handler::Bool { set: Some(set), .. } => match read_value::<u8>(reader)? {
+                0 => set(false),
+                1 => set(true),
+                _ => return_errno_with_message!(Errno::EINVAL, "the value is not a boolean"),
+            },
+            Handler::String { set: Some(set), .. } => {
+                let mut buf = vec![0u8; len];
+                reader.read_fallible(&mut VmWriter::from(buf.as_mut_slice()))?;
+                let value = core::str::from_utf8(&buf)
+                    .map_err(|_| Error::with_message(Errno::EINVAL, "the value is not UTF-8"))?;
+                set(value.trim())?;
+            }
+            _ => unreachable!(),
+        }
+
+        Ok(len)
+    }
+}
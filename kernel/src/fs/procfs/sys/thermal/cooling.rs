@@ -0,0 +1,86 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::format;
+
+use aster_thermal::CoolingDevice;
+
+use crate::{
+    fs::{
+        procfs::template::{DirOps, FileOps, ProcDir, ProcDirBuilder, ProcFileBuilder},
+        utils::{DirEntryVecExt, Inode},
+    },
+    prelude::*,
+};
+
+/// Represents the inode at `/proc/sys/thermal/cooling_deviceN`.
+pub struct CoolingDeviceDirOps(Arc<CoolingDevice>);
+
+impl CoolingDeviceDirOps {
+    pub fn new_inode(device: Arc<CoolingDevice>, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcDirBuilder::new(Self(device))
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+const ATTRS: [&str; 3] = ["cur_state", "max_state", "type"];
+
+impl DirOps for CoolingDeviceDirOps {
+    fn lookup_child(&self, this_ptr: Weak<dyn Inode>, name: &str) -> Result<Arc<dyn Inode>> {
+        let Some(attr) = ATTRS.into_iter().find(|attr| *attr == name) else {
+            return_errno!(Errno::ENOENT);
+        };
+        Ok(CoolingAttrFileOps::new_inode(
+            self.0.clone(),
+            attr,
+            this_ptr,
+        ))
+    }
+
+    fn populate_children(&self, this_ptr: Weak<dyn Inode>) {
+        let this = {
+            let this = this_ptr.upgrade().unwrap();
+            this.downcast_ref::<ProcDir<CoolingDeviceDirOps>>()
+                .unwrap()
+                .this()
+        };
+        let mut cached_children = this.cached_children().write();
+        for attr in ATTRS {
+            cached_children.put_entry_if_not_found(attr, || {
+                CoolingAttrFileOps::new_inode(self.0.clone(), attr, this_ptr.clone())
+            });
+        }
+    }
+}
+
+/// Represents the inode at `/proc/sys/thermal/cooling_deviceN/<attr>`.
+struct CoolingAttrFileOps {
+    device: Arc<CoolingDevice>,
+    attr: &'static str,
+}
+
+impl CoolingAttrFileOps {
+    pub fn new_inode(
+        device: Arc<CoolingDevice>,
+        attr: &'static str,
+        parent: Weak<dyn Inode>,
+    ) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self { device, attr })
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for CoolingAttrFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let output = match self.attr {
+            "cur_state" => format!("{}\n", self.device.cur_state()),
+            "max_state" => format!("{}\n", self.device.max_state()),
+            "type" => format!("{}\n", self.device.kind()),
+            _ => unreachable!(),
+        };
+        Ok(output.into_bytes())
+    }
+}
@@ -0,0 +1,73 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! This module offers `/proc/sys/thermal`, which works like `/sys/class/thermal` in Linux.
+//!
+//! Each thermal zone is a directory `thermal_zoneN`, and each cooling device is a directory
+//! `cooling_deviceN`.
+
+use alloc::format;
+
+use self::{cooling::CoolingDeviceDirOps, zone::ZoneDirOps};
+use super::read_value;
+use crate::{
+    fs::{
+        procfs::template::{DirOps, ProcDir, ProcDirBuilder},
+        utils::{DirEntryVecExt, Inode},
+    },
+    prelude::*,
+};
+
+mod cooling;
+mod zone;
+
+/// Represents the inode at `/proc/sys/thermal`.
+pub struct ThermalDirOps;
+
+impl ThermalDirOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcDirBuilder::new(Self).parent(parent).build().unwrap()
+    }
+}
+
+impl DirOps for ThermalDirOps {
+    fn lookup_child(&self, this_ptr: Weak<dyn Inode>, name: &str) -> Result<Arc<dyn Inode>> {
+        if let Some(zone) = name
+            .strip_prefix("thermal_zone")
+            .and_then(|index| index.parse::<usize>().ok())
+            .and_then(aster_thermal::get_zone)
+        {
+            return Ok(ZoneDirOps::new_inode(zone, this_ptr));
+        }
+
+        let Some(device) = name
+            .strip_prefix("cooling_device")
+            .and_then(|index| index.parse::<usize>().ok())
+            .and_then(aster_thermal::get_cooling_device)
+        else {
+            return_errno!(Errno::ENOENT);
+        };
+        Ok(CoolingDeviceDirOps::new_inode(device, this_ptr))
+    }
+
+    fn populate_children(&self, this_ptr: Weak<dyn Inode>) {
+        let this = {
+            let this = this_ptr.upgrade().unwrap();
+            this.downcast_ref::<ProcDir<ThermalDirOps>>()
+                .unwrap()
+                .this()
+        };
+        let mut cached_children = this.cached_children().write();
+        for zone in aster_thermal::all_zones() {
+            cached_children
+                .put_entry_if_not_found(&format!("thermal_zone{}", zone.index()), || {
+                    ZoneDirOps::new_inode(zone.clone(), this_ptr.clone())
+                });
+        }
+        for device in aster_thermal::all_cooling_devices() {
+            cached_children
+                .put_entry_if_not_found(&format!("cooling_device{}", device.index()), || {
+                    CoolingDeviceDirOps::new_inode(device.clone(), this_ptr.clone())
+                });
+        }
+    }
+}
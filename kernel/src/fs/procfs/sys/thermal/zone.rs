@@ -0,0 +1,186 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::format;
+
+use aster_thermal::{ThermalError, ThermalZone, ThermalZoneMode};
+
+use super::read_value;
+use crate::{
+    fs::{
+        procfs::template::{DirOps, FileOps, ProcDir, ProcDirBuilder, ProcFileBuilder},
+        utils::{DirEntryVecExt, Inode, InodeMode},
+    },
+    prelude::*,
+};
+
+/// Represents the inode at `/proc/sys/thermal/thermal_zoneN`.
+pub struct ZoneDirOps(Arc<ThermalZone>);
+
+impl ZoneDirOps {
+    pub fn new_inode(zone: Arc<ThermalZone>, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcDirBuilder::new(Self(zone))
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl DirOps for ZoneDirOps {
+    fn lookup_child(&self, this_ptr: Weak<dyn Inode>, name: &str) -> Result<Arc<dyn Inode>> {
+        let Some(attr) = ZoneAttr::from_name(name).filter(|attr| attr.exists_in(&self.0)) else {
+            return_errno!(Errno::ENOENT);
+        };
+        Ok(ZoneAttrFileOps::new_inode(self.0.clone(), attr, this_ptr))
+    }
+
+    fn populate_children(&self, this_ptr: Weak<dyn Inode>) {
+        let this = {
+            let this = this_ptr.upgrade().unwrap();
+            this.downcast_ref::<ProcDir<ZoneDirOps>>().unwrap().this()
+        };
+        let mut cached_children = this.cached_children().write();
+        for attr in ZoneAttr::all(&self.0) {
+            cached_children.put_entry_if_not_found(&attr.name(), || {
+                ZoneAttrFileOps::new_inode(self.0.clone(), attr, this_ptr.clone())
+            });
+        }
+    }
+}
+
+/// An attribute of a thermal zone, which has the same name and format as Linux.
+#[derive(Clone, Copy)]
+enum ZoneAttr {
+    Mode,
+    Policy,
+    Temp,
+    Type,
+    TripPointHyst(usize),
+    TripPointTemp(usize),
+    TripPointType(usize),
+}
+
+impl ZoneAttr {
+    fn all(zone: &ThermalZone) -> impl Iterator<Item = ZoneAttr> {
+        let trip_points = (0..zone.trips().len()).flat_map(|trip| {
+            [
+                ZoneAttr::TripPointHyst(trip),
+                ZoneAttr::TripPointTemp(trip),
+                ZoneAttr::TripPointType(trip),
+            ]
+        });
+        [
+            ZoneAttr::Mode,
+            ZoneAttr::Policy,
+            ZoneAttr::Temp,
+            ZoneAttr::Type,
+        ]
+        .into_iter()
+        .chain(trip_points)
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "mode" => return Some(ZoneAttr::Mode),
+            "policy" => return Some(ZoneAttr::Policy),
+            "temp" => return Some(ZoneAttr::Temp),
+            "type" => return Some(ZoneAttr::Type),
+            _ => {}
+        }
+
+        let (trip, suffix) = name.strip_prefix("trip_point_")?.split_once('_')?;
+        let trip = trip.parse().ok()?;
+        match suffix {
+            "hyst" => Some(ZoneAttr::TripPointHyst(trip)),
+            "temp" => Some(ZoneAttr::TripPointTemp(trip)),
+            "type" => Some(ZoneAttr::TripPointType(trip)),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> String {
+        match self {
+            ZoneAttr::Mode => "mode".to_string(),
+            ZoneAttr::Policy => "policy".to_string(),
+            ZoneAttr::Temp => "temp".to_string(),
+            ZoneAttr::Type => "type".to_string(),
+            ZoneAttr::TripPointHyst(trip) => format!("trip_point_{}_hyst", trip),
+            ZoneAttr::TripPointTemp(trip) => format!("trip_point_{}_temp", trip),
+            ZoneAttr::TripPointType(trip) => format!("trip_point_{}_type", trip),
+        }
+    }
+
+    fn exists_in(&self, zone: &ThermalZone) -> bool {
+        match self {
+            ZoneAttr::TripPointHyst(trip)
+            | ZoneAttr::TripPointTemp(trip)
+            | ZoneAttr::TripPointType(trip) => *trip < zone.trips().len(),
+            _ => true,
+        }
+    }
+}
+
+/// Represents the inode at `/proc/sys/thermal/thermal_zoneN/<attr>`.
+struct ZoneAttrFileOps {
+    zone: Arc<ThermalZone>,
+    attr: ZoneAttr,
+}
+
+impl ZoneAttrFileOps {
+    pub fn new_inode(
+        zone: Arc<ThermalZone>,
+        attr: ZoneAttr,
+        parent: Weak<dyn Inode>,
+    ) -> Arc<dyn Inode> {
+        let inode = ProcFileBuilder::new(Self { zone, attr })
+            .parent(parent)
+            .build()
+            .unwrap();
+        if matches!(attr, ZoneAttr::Mode) {
+            inode
+                .set_mode(InodeMode::from_bits_truncate(0o644))
+                .unwrap();
+        }
+        inode
+    }
+}
+
+impl FileOps for ZoneAttrFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let zone = &self.zone;
+        let output = match self.attr {
+            ZoneAttr::Mode => match zone.mode() {
+                ThermalZoneMode::Enabled => "enabled\n".to_string(),
+                ThermalZoneMode::Disabled => "disabled\n".to_string(),
+            },
+            ZoneAttr::Policy => format!("{}\n", aster_thermal::GOVERNOR_NAME),
+            ZoneAttr::Temp => match zone.temperature() {
+                Ok(temperature) => format!("{}\n", temperature),
+                Err(ThermalError::Busy) => {
+                    return_errno_with_message!(Errno::EAGAIN, "the temperature is not ready")
+                }
+                Err(_) => return_errno_with_message!(Errno::EIO, "cannot read the temperature"),
+            },
+            ZoneAttr::Type => format!("{}\n", zone.name()),
+            ZoneAttr::TripPointHyst(trip) => format!("{}\n", zone.trips()[trip].hysteresis),
+            ZoneAttr::TripPointTemp(trip) => format!("{}\n", zone.trips()[trip].temperature),
+            ZoneAttr::TripPointType(trip) => format!("{}\n", zone.trips()[trip].kind.name()),
+        };
+        Ok(output.into_bytes())
+    }
+
+    fn write_at(&self, _offset: usize, reader: &mut VmReader) -> Result<usize> {
+        let len = reader.remain();
+        if !matches!(self.attr, ZoneAttr::Mode) {
+            return_errno_with_message!(Errno::EPERM, "the attribute is read-only");
+        }
+
+        let mode: String = read_value(reader)?;
+        match mode.as_str() {
+            "enabled" => self.zone.set_mode(ThermalZoneMode::Enabled),
+            "disabled" => self.zone.set_mode(ThermalZoneMode::Disabled),
+            _ => return_errno_with_message!(Errno::EINVAL, "invalid mode"),
+        }
+
+        Ok(len)
+    }
+}
@@ -0,0 +1,145 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! This module offers the files in `/proc/sys/vm/ksm`, which control the kernel same-page
+//! merging (see [`crate::vm::vmar::ksm`]) like `/sys/kernel/mm/ksm` in Linux.
+
+use alloc::format;
+
+use super::read_value;
+use crate::{
+    fs::{
+        procfs::template::{DirOps, FileOps, ProcDir, ProcDirBuilder, ProcFileBuilder},
+        utils::{DirEntryVecExt, Inode, InodeMode},
+    },
+    prelude::*,
+    vm::vmar::ksm::{self, RunState},
+};
+
+/// Represents the inode at `/proc/sys/vm/ksm`.
+pub struct KsmDirOps;
+
+impl KsmDirOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcDirBuilder::new(Self).parent(parent).build().unwrap()
+    }
+}
+
+impl DirOps for KsmDirOps {
+    fn lookup_child(&self, this_ptr: Weak<dyn Inode>, name: &str) -> Result<Arc<dyn Inode>> {
+        let Some(tunable) = Tunable::ALL.into_iter().find(|t| t.name() == name) else {
+            return_errno!(Errno::ENOENT);
+        };
+        Ok(TunableFileOps::new_inode(tunable, this_ptr))
+    }
+
+    fn populate_children(&self, this_ptr: Weak<dyn Inode>) {
+        let this = {
+            let this = this_ptr.upgrade().unwrap();
+            this.downcast_ref::<ProcDir<KsmDirOps>>().unwrap().this()
+        };
+        let mut cached_children = this.cached_children().write();
+        for tunable in Tunable::ALL {
+            cached_children.put_entry_if_not_found(tunable.name(), || {
+                TunableFileOps::new_inode(tunable, this_ptr.clone())
+            });
+        }
+    }
+}
+
+/// A tunable or a statistic of KSM.
+#[derive(Clone, Copy)]
+enum Tunable {
+    /// The state of the KSM thread (0: stop, 1: run, 2: unmerge all the pages)
+    Run,
+    /// The number of the pages to scan in each batch
+    PagesToScan,
+    /// The time to sleep between the batches in milliseconds
+    SleepMillisecs,
+    /// The number of the KSM pages that are mapped
+    PagesShared,
+    /// The number of the other mappings of the KSM pages
+    PagesSharing,
+    /// The number of the times that all the mergeable pages have been scanned
+    FullScans,
+}
+
+impl Tunable {
+    const ALL: [Tunable; 6] = [
+        Tunable::Run,
+        Tunable::PagesToScan,
+        Tunable::SleepMillisecs,
+        Tunable::PagesShared,
+        Tunable::PagesSharing,
+        Tunable::FullScans,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Tunable::Run => "run",
+            Tunable::PagesToScan => "pages_to_scan",
+            Tunable::SleepMillisecs => "sleep_millisecs",
+            Tunable::PagesShared => "pages_shared",
+            Tunable::PagesSharing => "pages_sharing",
+            Tunable::FullScans => "full_scans",
+        }
+    }
+
+    fn is_writable(&self) -> bool {
+        matches!(
+            self,
+            Tunable::Run | Tunable::PagesToScan | Tunable::SleepMillisecs
+        )
+    }
+}
+
+/// Represents the inode at `/proc/sys/vm/ksm/<tunable>`.
+struct TunableFileOps(Tunable);
+
+impl TunableFileOps {
+    pub fn new_inode(tunable: Tunable, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        let inode = ProcFileBuilder::new(Self(tunable))
+            .parent(parent)
+            .build()
+            .unwrap();
+        if tunable.is_writable() {
+            inode
+                .set_mode(InodeMode::from_bits_truncate(0o644))
+                .unwrap();
+        }
+        inode
+    }
+}
+
+impl FileOps for TunableFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let value = match self.0 {
+            Tunable::Run => ksm::run_state() as u64,
+            Tunable::PagesToScan => ksm::pages_to_scan() as u64,
+            Tunable::SleepMillisecs => ksm::sleep_millisecs() as u64,
+            Tunable::PagesShared => ksm::pages_shared_and_sharing().0 as u64,
+            Tunable::PagesSharing => ksm::pages_shared_and_sharing().1 as u64,
+            Tunable::FullScans => ksm::full_scans(),
+        };
+        Ok(format!("{}\n", value).into_bytes())
+    }
+
+    fn write_at(&self, _offset: usize, reader: &mut VmReader) -> Result<usize> {
+        let len = reader.remain();
+        let value: u32 = read_value(reader)?;
+
+        match self.0 {
+            Tunable::Run => {
+                let state = RunState::try_from(value)
+                    .map_err(|_| Error::with_message(Errno::EINVAL, "invalid run state"))?;
+                ksm::set_run_state(state)?;
+            }
+            Tunable::PagesToScan => ksm::set_pages_to_scan(value)?,
+            Tunable::SleepMillisecs => ksm::set_sleep_millisecs(value),
+            Tunable::PagesShared | Tunable::PagesSharing | Tunable::FullScans => {
+                return_errno_with_message!(Errno::EPERM, "the statistic is read-only")
+            }
+        }
+
+        Ok(len)
+    }
+}
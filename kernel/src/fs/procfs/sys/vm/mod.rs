@@ -0,0 +1,62 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use self::ksm::KsmDirOps;
+use super::{
+    read_value,
+    sysctl::{self, Sysctl},
+};
+use crate::{
+    fs::{
+        procfs::template::{DirOps, ProcDir, ProcDirBuilder},
+        utils::{DirEntryVecExt, Inode},
+    },
+    prelude::*,
+    vm::hugetlb::{HugePagePool, HugePageSize},
+};
+
+mod ksm;
+
+/// The tunables in `/proc/sys/vm`.
+static SYSCTLS: [Sysctl; 1] = [
+    // The number of the huge pages of the default size in the pool (see `crate::vm::hugetlb`).
+    Sysctl::int(
+        "nr_hugepages",
+        || HugePagePool::get(HugePageSize::DEFAULT).stats().nr_total as i64,
+        |nr_pages| {
+            // Like Linux, the write succeeds even if fewer pages are allocated, which can be
+            // checked by reading the file.
+            HugePagePool::get(HugePageSize::DEFAULT).set_nr_total(nr_pages as usize);
+            Ok(())
+        },
+        0..=i64::MAX,
+    ),
+];
+
+/// Represents the inode at `/proc/sys/vm`.
+pub struct VmDirOps;
+
+impl VmDirOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcDirBuilder::new(Self).parent(parent).build().unwrap()
+    }
+}
+
+impl DirOps for VmDirOps {
+    fn lookup_child(&self, this_ptr: Weak<dyn Inode>, name: &str) -> Result<Arc<dyn Inode>> {
+        let inode = match name {
+            "ksm" => KsmDirOps::new_inode(this_ptr.clone()),
+            _ => sysctl::lookup(&SYSCTLS, name, this_ptr)?,
+        };
+        Ok(inode)
+    }
+
+    fn populate_children(&self, this_ptr: Weak<dyn Inode>) {
+        let this = {
+            let this = this_ptr.upgrade().unwrap();
+            this.downcast_ref::<ProcDir<VmDirOps>>().unwrap().this()
+        };
+        let mut cached_children = this.cached_children().write();
+        cached_children.put_entry_if_not_found("ksm", || KsmDirOps::new_inode(this_ptr.clone()));
+        sysctl::populate(&SYSCTLS, &mut cached_children, this_ptr.clone());
+    }
+}
@@ -11,7 +11,10 @@ use super::{Common, ProcFS};
 use crate::{
     fs::{
         path::{is_dot, is_dotdot},
-        utils::{DirentVisitor, FileSystem, Inode, InodeMode, InodeType, Metadata, MknodType},
+        utils::{
+            DirEntryVecExt, DirentVisitor, FileSystem, Inode, InodeMode, InodeType, Metadata,
+            MknodType,
+        },
     },
     prelude::*,
     process::{Gid, Uid},
@@ -93,8 +96,22 @@ impl<D: DirOps + 'static> Inode for ProcDir<D> {
         InodeType::Dir
     }
 
-    fn create(&self, _name: &str, _type_: InodeType, _mode: InodeMode) -> Result<Arc<dyn Inode>> {
-        Err(Error::new(Errno::EPERM))
+    fn create(&self, name: &str, type_: InodeType, mode: InodeMode) -> Result<Arc<dyn Inode>> {
+        if type_ != InodeType::Dir {
+            return_errno!(Errno::EPERM);
+        }
+
+        let mut cached_children = self.cached_children.write();
+        if cached_children
+            .iter()
+            .any(|(child_name, _)| child_name.as_str() == name)
+            || self.inner.lookup_child(self.this.clone(), name).is_ok()
+        {
+            return_errno!(Errno::EEXIST);
+        }
+        let inode = self.inner.create_dir(self.this.clone(), name, mode)?;
+        cached_children.put((String::from(name), inode.clone()));
+        Ok(inode)
     }
 
     fn mknod(&self, _name: &str, _mode: InodeMode, type_: MknodType) -> Result<Arc<dyn Inode>> {
@@ -150,8 +167,11 @@ impl<D: DirOps + 'static> Inode for ProcDir<D> {
         Err(Error::new(Errno::EPERM))
     }
 
-    fn rmdir(&self, _name: &str) -> Result<()> {
-        Err(Error::new(Errno::EPERM))
+    fn rmdir(&self, name: &str) -> Result<()> {
+        let mut cached_children = self.cached_children.write();
+        self.inner.remove_dir(name)?;
+        cached_children.remove_entry_by_name(name);
+        Ok(())
     }
 
     fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>> {
@@ -189,4 +209,21 @@ pub trait DirOps: Sync + Send {
     }
 
     fn populate_children(&self, this_ptr: Weak<dyn Inode>) {}
+
+    /// Creates a child directory, which is called by `mkdir`.
+    ///
+    /// The name is guaranteed not to be taken by other children.
+    fn create_dir(
+        &self,
+        this_ptr: Weak<dyn Inode>,
+        name: &str,
+        mode: InodeMode,
+    ) -> Result<Arc<dyn Inode>> {
+        Err(Error::new(Errno::EPERM))
+    }
+
+    /// Removes a child directory, which is called by `rmdir`.
+    fn remove_dir(&self, name: &str) -> Result<()> {
+        Err(Error::new(Errno::EPERM))
+    }
 }
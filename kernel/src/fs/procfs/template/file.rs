@@ -6,7 +6,10 @@ use inherit_methods_macro::inherit_methods;
 
 use super::{Common, ProcFS};
 use crate::{
-    fs::utils::{FileSystem, Inode, InodeMode, InodeType, IoctlCmd, Metadata},
+    fs::{
+        inode_handle::FileIo,
+        utils::{FileSystem, Inode, InodeMode, InodeType, IoctlCmd, Metadata},
+    },
     prelude::*,
     process::{Gid, Uid},
 };
@@ -54,7 +57,12 @@ impl<F: FileOps + 'static> Inode for ProcFile<F> {
     fn set_ctime(&self, time: Duration);
     fn fs(&self) -> Arc<dyn FileSystem>;
 
-    fn resize(&self, _new_size: usize) -> Result<()> {
+    fn resize(&self, new_size: usize) -> Result<()> {
+        // Truncating is ignored, so that the writable files can be opened with `O_TRUNC`, e.g.,
+        // by the redirections of shells.
+        if new_size == 0 {
+            return Ok(());
+        }
         Err(Error::new(Errno::EPERM))
     }
 
@@ -63,24 +71,23 @@ impl<F: FileOps + 'static> Inode for ProcFile<F> {
     }
 
     fn read_at(&self, offset: usize, writer: &mut VmWriter) -> Result<usize> {
-        let data = self.inner.data()?;
-        let start = data.len().min(offset);
-        let end = data.len().min(offset + writer.avail());
-        let len = end - start;
-        writer.write_fallible(&mut (&data[start..end]).into())?;
-        Ok(len)
+        self.inner.read_at(offset, writer)
     }
 
     fn read_direct_at(&self, offset: usize, writer: &mut VmWriter) -> Result<usize> {
         self.read_at(offset, writer)
     }
 
-    fn write_at(&self, _offset: usize, _reader: &mut VmReader) -> Result<usize> {
-        Err(Error::new(Errno::EPERM))
+    fn write_at(&self, offset: usize, reader: &mut VmReader) -> Result<usize> {
+        self.inner.write_at(offset, reader)
     }
 
-    fn write_direct_at(&self, _offset: usize, _reader: &mut VmReader) -> Result<usize> {
-        Err(Error::new(Errno::EPERM))
+    fn write_direct_at(&self, offset: usize, reader: &mut VmReader) -> Result<usize> {
+        self.write_at(offset, reader)
+    }
+
+    fn open(&self) -> Result<Option<Arc<dyn FileIo>>> {
+        self.inner.open()
     }
 
     fn read_link(&self) -> Result<String> {
@@ -102,4 +109,28 @@ impl<F: FileOps + 'static> Inode for ProcFile<F> {
 
 pub trait FileOps: Sync + Send {
     fn data(&self) -> Result<Vec<u8>>;
+
+    /// Reads the file at `offset`, which reads from the whole data by default.
+    ///
+    /// The files that are too large to be generated at once (e.g., `/proc/vmcore`) should
+    /// override it to read the required part only.
+    fn read_at(&self, offset: usize, writer: &mut VmWriter) -> Result<usize> {
+        let data = self.data()?;
+        let start = data.len().min(offset);
+        let end = data.len().min(offset + writer.avail());
+        let len = end - start;
+        writer.write_fallible(&mut (&data[start..end]).into())?;
+        Ok(len)
+    }
+
+    /// Writes the data to the file, which is only supported by the writable files.
+    fn write_at(&self, _offset: usize, _reader: &mut VmReader) -> Result<usize> {
+        Err(Error::new(Errno::EPERM))
+    }
+
+    /// Opens the file, which can return a [`FileIo`] if each opened file needs its own states
+    /// (e.g., the triggers of `/proc/pressure/*`).
+    fn open(&self) -> Result<Option<Arc<dyn FileIo>>> {
+        Ok(None)
+    }
 }
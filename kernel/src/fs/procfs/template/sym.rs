@@ -90,8 +90,17 @@ impl<S: SymOps + 'static> Inode for ProcSym<S> {
     fn is_dentry_cacheable(&self) -> bool {
         !self.common.is_volatile()
     }
+
+    fn is_magic_link(&self) -> bool {
+        self.inner.is_magic_link()
+    }
 }
 
 pub trait SymOps: Sync + Send {
     fn read_link(&self) -> Result<String>;
+
+    /// Returns whether the link refers to an object in the kernel rather than a path.
+    fn is_magic_link(&self) -> bool {
+        false
+    }
 }
@@ -0,0 +1,37 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! This module offers `/proc/vmcore` file support, which exports the memory of the
+//! crashed kernel as an ELF core file if the kernel is booted as a capture kernel.
+//!
+//! Reference: <https://www.kernel.org/doc/html/latest/admin-guide/kdump/kdump.html>
+
+use crate::{
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::Inode,
+    },
+    kexec::vmcore::Vmcore,
+    prelude::*,
+};
+
+/// Represents the inode at `/proc/vmcore`.
+pub struct VmcoreFileOps(&'static Vmcore);
+
+impl VmcoreFileOps {
+    pub fn new_inode(vmcore: &'static Vmcore, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self(vmcore))
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for VmcoreFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        return_errno_with_message!(Errno::EINVAL, "the file is too large to be read at once");
+    }
+
+    fn read_at(&self, offset: usize, writer: &mut VmWriter) -> Result<usize> {
+        self.0.read_at(offset, writer)
+    }
+}
@@ -0,0 +1,372 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#![expect(unused_variables)]
+
+//! The pstore filesystem.
+//!
+//! The filesystem exports the records in the persistent storage that were written in the
+//! previous boots (see [`crate::pstore`]), e.g., the kernel logs dumped on panics. It is
+//! usually mounted at "/sys/fs/pstore". Each record is a read-only file, and removing the
+//! file erases the record.
+
+use core::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use spin::Once;
+
+use crate::{
+    fs::utils::{
+        DirentVisitor, FileSystem, FsFlags, Inode, InodeMode, InodeType, Metadata, SuperBlock,
+        NAME_MAX,
+    },
+    prelude::*,
+    process::{Gid, Uid},
+    pstore::{self, Record},
+    time::clocks::RealTimeCoarseClock,
+};
+
+const PSTORE_MAGIC: u64 = 0x6165676c;
+const BLOCK_SIZE: usize = 4096;
+const ROOT_INO: u64 = 1;
+
+/// The pstore filesystem.
+///
+/// Like Linux, there is only one instance of the filesystem, so all the mounts of the
+/// filesystem share the same records.
+pub struct PstoreFs {
+    sb: SuperBlock,
+    root: Arc<RootInode>,
+}
+
+impl PstoreFs {
+    /// Returns the instance of the filesystem.
+    pub fn singleton() -> &'static Arc<Self> {
+        static PSTORE_FS: Once<Arc<PstoreFs>> = Once::new();
+
+        PSTORE_FS.call_once(|| {
+            Arc::new_cyclic(|weak_self| Self {
+                sb: SuperBlock::new(PSTORE_MAGIC, BLOCK_SIZE, NAME_MAX),
+                root: RootInode::new(weak_self.clone()),
+            })
+        })
+    }
+}
+
+impl FileSystem for PstoreFs {
+    fn sync(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn root_inode(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+
+    fn sb(&self) -> SuperBlock {
+        self.sb.clone()
+    }
+
+    fn flags(&self) -> FsFlags {
+        FsFlags::empty()
+    }
+}
+
+struct RootInode {
+    records: RwLock<BTreeMap<String, Arc<RecordInode>>>,
+    metadata: RwLock<Metadata>,
+    fs: Weak<PstoreFs>,
+}
+
+impl RootInode {
+    fn new(fs: Weak<PstoreFs>) -> Arc<Self> {
+        let next_ino = AtomicU64::new(ROOT_INO + 1);
+        let records = pstore::records()
+            .into_iter()
+            .map(|record| {
+                let ino = next_ino.fetch_add(1, Ordering::Relaxed);
+                (record.name(), RecordInode::new(ino, record, fs.clone()))
+            })
+            .collect();
+
+        let mut metadata =
+            Metadata::new_dir(ROOT_INO, InodeMode::from_bits_truncate(0o750), BLOCK_SIZE);
+        let now = RealTimeCoarseClock::get().read_time();
+        metadata.atime = now;
+        metadata.mtime = now;
+        metadata.ctime = now;
+
+        Arc::new(Self {
+            records: RwLock::new(records),
+            metadata: RwLock::new(metadata),
+            fs,
+        })
+    }
+}
+
+impl Inode for RootInode {
+    fn size(&self) -> usize {
+        self.metadata.read().size
+    }
+
+    fn resize(&self, new_size: usize) -> Result<()> {
+        Err(Error::new(Errno::EISDIR))
+    }
+
+    fn metadata(&self) -> Metadata {
+        *self.metadata.read()
+    }
+
+    fn ino(&self) -> u64 {
+        ROOT_INO
+    }
+
+    fn type_(&self) -> InodeType {
+        InodeType::Dir
+    }
+
+    fn mode(&self) -> Result<InodeMode> {
+        Ok(self.metadata.read().mode)
+    }
+
+    fn set_mode(&self, mode: InodeMode) -> Result<()> {
+        self.metadata.write().mode = mode;
+        Ok(())
+    }
+
+    fn owner(&self) -> Result<Uid> {
+        Ok(self.metadata.read().uid)
+    }
+
+    fn set_owner(&self, uid: Uid) -> Result<()> {
+        self.metadata.write().uid = uid;
+        Ok(())
+    }
+
+    fn group(&self) -> Result<Gid> {
+        Ok(self.metadata.read().gid)
+    }
+
+    fn set_group(&self, gid: Gid) -> Result<()> {
+        self.metadata.write().gid = gid;
+        Ok(())
+    }
+
+    fn atime(&self) -> Duration {
+        self.metadata.read().atime
+    }
+
+    fn set_atime(&self, time: Duration) {
+        self.metadata.write().atime = time;
+    }
+
+    fn mtime(&self) -> Duration {
+        self.metadata.read().mtime
+    }
+
+    fn set_mtime(&self, time: Duration) {
+        self.metadata.write().mtime = time;
+    }
+
+    fn ctime(&self) -> Duration {
+        self.metadata.read().ctime
+    }
+
+    fn set_ctime(&self, time: Duration) {
+        self.metadata.write().ctime = time;
+    }
+
+    fn create(&self, name: &str, type_: InodeType, mode: InodeMode) -> Result<Arc<dyn Inode>> {
+        return_errno_with_message!(Errno::EPERM, "the records cannot be created");
+    }
+
+    fn readdir_at(&self, offset: usize, visitor: &mut dyn DirentVisitor) -> Result<usize> {
+        let try_readdir = |offset: &mut usize, visitor: &mut dyn DirentVisitor| -> Result<()> {
+            // Read the 2 special entries.
+            if *offset == 0 {
+                visitor.visit(".", ROOT_INO, InodeType::Dir, *offset)?;
+                *offset += 1;
+            }
+            if *offset == 1 {
+                visitor.visit("..", ROOT_INO, InodeType::Dir, *offset)?;
+                *offset += 1;
+            }
+
+            // Read the records.
+            let records = self.records.read();
+            for (idx, (name, inode)) in records.iter().enumerate().skip(*offset - 2) {
+                visitor.visit(name, inode.ino(), InodeType::File, idx + 2)?;
+                *offset = idx + 3;
+            }
+            Ok(())
+        };
+
+        let mut iterate_offset = offset;
+        match try_readdir(&mut iterate_offset, visitor) {
+            Err(e) if offset == iterate_offset => Err(e),
+            _ => Ok(iterate_offset - offset),
+        }
+    }
+
+    fn link(&self, old: &Arc<dyn Inode>, name: &str) -> Result<()> {
+        Err(Error::new(Errno::EPERM))
+    }
+
+    fn unlink(&self, name: &str) -> Result<()> {
+        let Some(inode) = self.records.write().remove(name) else {
+            return_errno_with_message!(Errno::ENOENT, "the record does not exist");
+        };
+        pstore::erase(&inode.record);
+
+        let now = RealTimeCoarseClock::get().read_time();
+        let mut metadata = self.metadata.write();
+        metadata.mtime = now;
+        metadata.ctime = now;
+        Ok(())
+    }
+
+    fn rmdir(&self, name: &str) -> Result<()> {
+        Err(Error::new(Errno::ENOTDIR))
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>> {
+        let inode: Arc<dyn Inode> =
+            match name {
+                "." | ".." => self.fs().root_inode(),
+                name => self.records.read().get(name).cloned().ok_or_else(|| {
+                    Error::with_message(Errno::ENOENT, "the record does not exist")
+                })?,
+            };
+        Ok(inode)
+    }
+
+    fn rename(&self, old_name: &str, target: &Arc<dyn Inode>, new_name: &str) -> Result<()> {
+        Err(Error::new(Errno::EPERM))
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        self.fs.upgrade().unwrap()
+    }
+}
+
+/// An inode of a record.
+struct RecordInode {
+    record: Arc<Record>,
+    metadata: RwLock<Metadata>,
+    fs: Weak<PstoreFs>,
+}
+
+impl RecordInode {
+    fn new(ino: u64, record: Arc<Record>, fs: Weak<PstoreFs>) -> Arc<Self> {
+        let mut metadata =
+            Metadata::new_file(ino, InodeMode::from_bits_truncate(0o440), BLOCK_SIZE);
+        metadata.size = record.data().len();
+        metadata.blocks = record.data().len().div_ceil(BLOCK_SIZE);
+        metadata.atime = record.time();
+        metadata.mtime = record.time();
+        metadata.ctime = record.time();
+
+        Arc::new(Self {
+            record,
+            metadata: RwLock::new(metadata),
+            fs,
+        })
+    }
+}
+
+impl Inode for RecordInode {
+    fn size(&self) -> usize {
+        self.record.data().len()
+    }
+
+    fn resize(&self, new_size: usize) -> Result<()> {
+        Err(Error::new(Errno::EPERM))
+    }
+
+    fn metadata(&self) -> Metadata {
+        *self.metadata.read()
+    }
+
+    fn ino(&self) -> u64 {
+        self.metadata.read().ino
+    }
+
+    fn type_(&self) -> InodeType {
+        InodeType::File
+    }
+
+    fn mode(&self) -> Result<InodeMode> {
+        Ok(self.metadata.read().mode)
+    }
+
+    fn set_mode(&self, mode: InodeMode) -> Result<()> {
+        self.metadata.write().mode = mode;
+        Ok(())
+    }
+
+    fn owner(&self) -> Result<Uid> {
+        Ok(self.metadata.read().uid)
+    }
+
+    fn set_owner(&self, uid: Uid) -> Result<()> {
+        self.metadata.write().uid = uid;
+        Ok(())
+    }
+
+    fn group(&self) -> Result<Gid> {
+        Ok(self.metadata.read().gid)
+    }
+
+    fn set_group(&self, gid: Gid) -> Result<()> {
+        self.metadata.write().gid = gid;
+        Ok(())
+    }
+
+    fn atime(&self) -> Duration {
+        self.metadata.read().atime
+    }
+
+    fn set_atime(&self, time: Duration) {
+        self.metadata.write().atime = time;
+    }
+
+    fn mtime(&self) -> Duration {
+        self.metadata.read().mtime
+    }
+
+    fn set_mtime(&self, time: Duration) {
+        self.metadata.write().mtime = time;
+    }
+
+    fn ctime(&self) -> Duration {
+        self.metadata.read().ctime
+    }
+
+    fn set_ctime(&self, time: Duration) {
+        self.metadata.write().ctime = time;
+    }
+
+    fn read_at(&self, offset: usize, writer: &mut VmWriter) -> Result<usize> {
+        let Some(bytes) = self.record.data().get(offset..) else {
+            return Ok(0);
+        };
+        let len = writer.write_fallible(&mut bytes.into())?;
+        Ok(len)
+    }
+
+    fn read_direct_at(&self, offset: usize, writer: &mut VmWriter) -> Result<usize> {
+        self.read_at(offset, writer)
+    }
+
+    fn write_at(&self, offset: usize, reader: &mut VmReader) -> Result<usize> {
+        return_errno_with_message!(Errno::EPERM, "the records cannot be written");
+    }
+
+    fn write_direct_at(&self, offset: usize, reader: &mut VmReader) -> Result<usize> {
+        self.write_at(offset, reader)
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        self.fs.upgrade().unwrap()
+    }
+}
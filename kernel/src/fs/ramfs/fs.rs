@@ -15,7 +15,11 @@ use ostd::{
     sync::{PreemptDisabled, RwLockWriteGuard},
 };
 
-use super::{xattr::RamXattr, *};
+use super::{
+    tmpfs::{RamUsage, TmpfsMountOptions},
+    xattr::RamXattr,
+    *,
+};
 use crate::{
     events::IoEvents,
     fs::{
@@ -43,19 +47,36 @@ pub struct RamFS {
     root: Arc<RamInode>,
     /// An inode allocator
     inode_allocator: AtomicU64,
+    /// The used pages and inodes, which are limited for tmpfs
+    usage: RamUsage,
 }
 
 impl RamFS {
     pub fn new() -> Arc<Self> {
+        Self::new_with_usage(
+            InodeMode::from_bits_truncate(0o755),
+            Uid::new_root(),
+            Gid::new_root(),
+            RamUsage::new(None, None),
+        )
+    }
+
+    /// Creates a ramfs whose pages and inodes are limited by the mount options of tmpfs.
+    pub fn new_tmpfs(options: TmpfsMountOptions) -> Arc<Self> {
+        Self::new_with_usage(
+            options.mode,
+            options.uid,
+            options.gid,
+            RamUsage::new(options.max_pages, options.max_inodes),
+        )
+    }
+
+    fn new_with_usage(root_mode: InodeMode, uid: Uid, gid: Gid, usage: RamUsage) -> Arc<Self> {
         Arc::new_cyclic(|weak_fs| Self {
             sb: SuperBlock::new(RAMFS_MAGIC, BLOCK_SIZE, NAME_MAX),
             root: Arc::new_cyclic(|weak_root| RamInode {
                 inner: Inner::new_dir(weak_root.clone(), weak_root.clone()),
-                metadata: SpinLock::new(InodeMeta::new_dir(
-                    InodeMode::from_bits_truncate(0o755),
-                    Uid::new_root(),
-                    Gid::new_root(),
-                )),
+                metadata: SpinLock::new(InodeMeta::new_dir(root_mode, uid, gid)),
                 ino: ROOT_INO,
                 typ: InodeType::Dir,
                 this: weak_root.clone(),
@@ -64,6 +85,7 @@ impl RamFS {
                 xattr: RamXattr::new(),
             }),
             inode_allocator: AtomicU64::new(ROOT_INO + 1),
+            usage,
         })
     }
 
@@ -83,7 +105,9 @@ impl FileSystem for RamFS {
     }
 
     fn sb(&self) -> SuperBlock {
-        self.sb.clone()
+        let mut sb = self.sb.clone();
+        self.usage.fill_super_block(&mut sb);
+        sb
     }
 
     fn flags(&self) -> FsFlags {
@@ -190,6 +214,7 @@ struct InodeMeta {
     atime: Duration,
     mtime: Duration,
     ctime: Duration,
+    btime: Duration,
     mode: InodeMode,
     nlinks: usize,
     uid: Uid,
@@ -205,6 +230,7 @@ impl InodeMeta {
             atime: now,
             mtime: now,
             ctime: now,
+            btime: now,
             mode,
             nlinks: 1,
             uid,
@@ -220,6 +246,7 @@ impl InodeMeta {
             atime: now,
             mtime: now,
             ctime: now,
+            btime: now,
             mode,
             nlinks: NUM_SPECIAL_ENTRIES,
             uid,
@@ -477,6 +504,27 @@ impl RamInode {
         })
     }
 
+    /// Charges the file system for the blocks of a regular file to grow to `new_blocks`.
+    fn grow_blocks(&self, new_blocks: usize) -> Result<()> {
+        let fs = self.fs.upgrade().unwrap();
+        let mut inode_meta = self.metadata.lock();
+        if new_blocks > inode_meta.blocks {
+            fs.usage.charge_pages(new_blocks - inode_meta.blocks)?;
+            inode_meta.blocks = new_blocks;
+        }
+        Ok(())
+    }
+
+    /// Uncharges the blocks of a regular file beyond `new_blocks`.
+    fn shrink_blocks(&self, new_blocks: usize) {
+        let fs = self.fs.upgrade().unwrap();
+        let mut inode_meta = self.metadata.lock();
+        if new_blocks < inode_meta.blocks {
+            fs.usage.uncharge_pages(inode_meta.blocks - new_blocks);
+            inode_meta.blocks = new_blocks;
+        }
+    }
+
     fn find(&self, name: &str) -> Result<Arc<Self>> {
         if self.typ != InodeType::Dir {
             return_errno_with_message!(Errno::ENOTDIR, "self is not dir");
@@ -514,6 +562,19 @@ impl PageCacheBackend for RamInode {
     }
 }
 
+impl Drop for RamInode {
+    fn drop(&mut self) {
+        // The file system is being dropped if it cannot be upgraded.
+        let Some(fs) = self.fs.upgrade() else {
+            return;
+        };
+        fs.usage.uncharge_inode();
+        if self.typ == InodeType::File {
+            fs.usage.uncharge_pages(self.metadata.get_mut().blocks);
+        }
+    }
+}
+
 impl Inode for RamInode {
     fn page_cache(&self) -> Option<Vmo<Full>> {
         self.inner
@@ -565,6 +626,7 @@ impl Inode for RamInode {
                 let should_expand_size = new_size > file_size;
                 let new_size_aligned = new_size.align_up(BLOCK_SIZE);
                 if should_expand_size {
+                    self.grow_blocks(new_size_aligned / BLOCK_SIZE)?;
                     page_cache.resize(new_size_aligned)?;
                 }
                 page_cache.pages().write(offset, reader)?;
@@ -575,7 +637,6 @@ impl Inode for RamInode {
                 inode_meta.set_ctime(now);
                 if should_expand_size {
                     inode_meta.size = new_size;
-                    inode_meta.blocks = new_size_aligned / BLOCK_SIZE;
                 }
                 write_len
             }
@@ -612,8 +673,11 @@ impl Inode for RamInode {
             return Ok(());
         }
 
+        let new_blocks = new_size.align_up(BLOCK_SIZE) / BLOCK_SIZE;
+        self.grow_blocks(new_blocks)?;
         let page_cache = self.inner.as_file().unwrap();
         page_cache.resize(new_size)?;
+        self.shrink_blocks(new_blocks);
 
         let now = now();
         let mut inode_meta = self.metadata.lock();
@@ -701,22 +765,15 @@ impl Inode for RamInode {
             return_errno_with_message!(Errno::EEXIST, "entry exists");
         }
 
+        let fs = self.fs.upgrade().unwrap();
+        fs.usage.charge_inode()?;
         let new_inode = match type_ {
             MknodType::CharDeviceNode(device) | MknodType::BlockDeviceNode(device) => {
-                RamInode::new_device(
-                    &self.fs.upgrade().unwrap(),
-                    mode,
-                    Uid::new_root(),
-                    Gid::new_root(),
-                    device,
-                )
+                RamInode::new_device(&fs, mode, Uid::new_root(), Gid::new_root(), device)
+            }
+            MknodType::NamedPipeNode => {
+                RamInode::new_named_pipe(&fs, mode, Uid::new_root(), Gid::new_root())
             }
-            MknodType::NamedPipeNode => RamInode::new_named_pipe(
-                &self.fs.upgrade().unwrap(),
-                mode,
-                Uid::new_root(),
-                Gid::new_root(),
-            ),
         };
 
         let mut self_dir = self_dir.upgrade();
@@ -748,6 +805,7 @@ impl Inode for RamInode {
         }
 
         let fs = self.fs.upgrade().unwrap();
+        fs.usage.charge_inode()?;
         let new_inode = match type_ {
             InodeType::File => RamInode::new_file(&fs, mode, Uid::new_root(), Gid::new_root()),
             InodeType::SymLink => {
@@ -1082,6 +1140,112 @@ impl Inode for RamInode {
         Ok(())
     }
 
+    fn exchange(&self, old_name: &str, target: &Arc<dyn Inode>, new_name: &str) -> Result<()> {
+        if is_dot_or_dotdot(old_name) || is_dot_or_dotdot(new_name) {
+            return_errno_with_message!(Errno::EBUSY, "old_name or new_name is . or ..");
+        }
+
+        let target = target
+            .downcast_ref::<RamInode>()
+            .ok_or(Error::new(Errno::EXDEV))?;
+
+        if !Arc::ptr_eq(&self.fs(), &target.fs()) {
+            return_errno_with_message!(Errno::EXDEV, "not same fs");
+        }
+        if self.typ != InodeType::Dir {
+            return_errno_with_message!(Errno::ENOTDIR, "self is not dir");
+        }
+        if target.typ != InodeType::Dir {
+            return_errno_with_message!(Errno::ENOTDIR, "target is not dir");
+        }
+
+        let now = now();
+        // Exchange in the same directory
+        if self.ino == target.ino {
+            let mut self_dir = self.inner.as_direntry().unwrap().write();
+            let (src_idx, src_inode) = self_dir
+                .get_entry(old_name)
+                .ok_or(Error::new(Errno::ENOENT))?;
+            let (dst_idx, dst_inode) = self_dir
+                .get_entry(new_name)
+                .ok_or(Error::new(Errno::ENOENT))?;
+            self_dir.substitute_entry(src_idx, (CStr256::from(old_name), dst_inode.clone()));
+            self_dir.substitute_entry(dst_idx, (CStr256::from(new_name), src_inode.clone()));
+            drop(self_dir);
+
+            let mut self_meta = self.metadata.lock();
+            self_meta.set_mtime(now);
+            self_meta.set_ctime(now);
+            drop(self_meta);
+            src_inode.set_ctime(now);
+            dst_inode.set_ctime(now);
+            return Ok(());
+        }
+
+        // Or exchange across different directories
+        let (mut self_dir, mut target_dir) = write_lock_two_direntries_by_ino(
+            (self.ino, self.inner.as_direntry().unwrap()),
+            (target.ino, target.inner.as_direntry().unwrap()),
+        );
+        let self_inode_arc = self.this.upgrade().unwrap();
+        let target_inode_arc = target.this.upgrade().unwrap();
+        let (src_idx, src_inode) = self_dir
+            .get_entry(old_name)
+            .ok_or(Error::new(Errno::ENOENT))?;
+        let (dst_idx, dst_inode) = target_dir
+            .get_entry(new_name)
+            .ok_or(Error::new(Errno::ENOENT))?;
+        // Avoid moving a directory to a subdirectory of itself
+        if Arc::ptr_eq(&src_inode, &target_inode_arc) || Arc::ptr_eq(&dst_inode, &self_inode_arc) {
+            return_errno!(Errno::EINVAL);
+        }
+        self_dir.substitute_entry(src_idx, (CStr256::from(old_name), dst_inode.clone()));
+        target_dir.substitute_entry(dst_idx, (CStr256::from(new_name), src_inode.clone()));
+        drop(self_dir);
+        drop(target_dir);
+
+        let src_is_dir = src_inode.typ == InodeType::Dir;
+        let dst_is_dir = dst_inode.typ == InodeType::Dir;
+        let mut self_meta = self.metadata.lock();
+        if src_is_dir && !dst_is_dir {
+            self_meta.dec_nlinks();
+        } else if !src_is_dir && dst_is_dir {
+            self_meta.inc_nlinks();
+        }
+        self_meta.set_mtime(now);
+        self_meta.set_ctime(now);
+        drop(self_meta);
+        let mut target_meta = target.metadata.lock();
+        if src_is_dir && !dst_is_dir {
+            target_meta.inc_nlinks();
+        } else if !src_is_dir && dst_is_dir {
+            target_meta.dec_nlinks();
+        }
+        target_meta.set_mtime(now);
+        target_meta.set_ctime(now);
+        drop(target_meta);
+        src_inode.set_ctime(now);
+        dst_inode.set_ctime(now);
+
+        if src_is_dir {
+            src_inode
+                .inner
+                .as_direntry()
+                .unwrap()
+                .write()
+                .set_parent(target.this.clone());
+        }
+        if dst_is_dir {
+            dst_inode
+                .inner
+                .as_direntry()
+                .unwrap()
+                .write()
+                .set_parent(self.this.clone());
+        }
+        Ok(())
+    }
+
     fn read_link(&self) -> Result<String> {
         if self.typ != InodeType::SymLink {
             return_errno_with_message!(Errno::EINVAL, "self is not symlink");
@@ -1121,6 +1285,7 @@ impl Inode for RamInode {
             atime: inode_metadata.atime,
             mtime: inode_metadata.mtime,
             ctime: inode_metadata.ctime,
+            btime: Some(inode_metadata.btime),
             type_: self.typ,
             mode: inode_metadata.mode,
             nlinks: inode_metadata.nlinks,
@@ -3,8 +3,11 @@
 //! Ramfs based on PageCache
 
 pub use fs::RamFS;
+pub(in crate::fs) use tmpfs::parse_number;
+pub use tmpfs::TmpfsMountOptions;
 
 mod fs;
+mod tmpfs;
 mod xattr;
 
 const RAMFS_MAGIC: u64 = 0x0102_1994;
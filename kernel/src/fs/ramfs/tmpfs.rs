@@ -0,0 +1,209 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The limits of ramfs when it is mounted as tmpfs.
+//!
+//! The pages of the regular files and the inodes are charged to the file system,
+//! and the charges beyond the limits fail with `ENOSPC`. So a runaway `/tmp`
+//! cannot exhaust the memory of the whole system.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use super::BLOCK_SIZE;
+use crate::{
+    fs::utils::{InodeMode, SuperBlock},
+    prelude::*,
+    process::{Gid, Uid},
+};
+
+/// The mount options of tmpfs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TmpfsMountOptions {
+    /// The maximum number of pages, or `None` if unlimited.
+    ///
+    /// It defaults to half of the physical memory.
+    pub max_pages: Option<usize>,
+    /// The maximum number of inodes, or `None` if unlimited.
+    ///
+    /// It defaults to the number of the physical pages divided by two.
+    pub max_inodes: Option<usize>,
+    /// The permission bits of the root directory.
+    pub mode: InodeMode,
+    pub uid: Uid,
+    pub gid: Gid,
+}
+
+impl TmpfsMountOptions {
+    /// Parses the options like `size=64m,nr_inodes=1k,mode=1777`.
+    ///
+    /// The `size` may also be a percentage of the physical memory, e.g., `size=25%`.
+    /// A zero `size` or `nr_inodes` stands for no limit.
+    pub fn parse(options: &str) -> Result<Self> {
+        let mut max_pages = None;
+        let mut max_inodes = None;
+        let mut mount_options = Self {
+            max_pages: None,
+            max_inodes: None,
+            mode: InodeMode::from_bits_truncate(0o1777),
+            uid: Uid::new_root(),
+            gid: Gid::new_root(),
+        };
+
+        for option in options.split(',').filter(|option| !option.is_empty()) {
+            let (key, value) = option.split_once('=').unwrap_or((option, ""));
+            match key {
+                "size" => {
+                    let bytes = match value.strip_suffix('%') {
+                        Some(percent) => {
+                            let percent = parse_number(percent)?;
+                            crate::vm::mem_total() / 100 * percent
+                        }
+                        None => parse_number(value)?,
+                    };
+                    max_pages = Some(bytes.div_ceil(BLOCK_SIZE));
+                }
+                "nr_inodes" => max_inodes = Some(parse_number(value)?),
+                "mode" => {
+                    let mode = u16::from_str_radix(value, 8)
+                        .map_err(|_| Error::with_message(Errno::EINVAL, "invalid mode"))?;
+                    mount_options.mode = InodeMode::from_bits_truncate(mode);
+                }
+                "uid" => mount_options.uid = Uid::new(parse_id(value)?),
+                "gid" => mount_options.gid = Gid::new(parse_id(value)?),
+                _ => {
+                    warn!("unsupported tmpfs mount option: {}", key);
+                    return_errno_with_message!(Errno::EINVAL, "unsupported mount option");
+                }
+            }
+        }
+
+        let default_pages = || crate::vm::mem_total() / BLOCK_SIZE / 2;
+        mount_options.max_pages =
+            Some(max_pages.unwrap_or_else(default_pages)).filter(|pages| *pages != 0);
+        mount_options.max_inodes =
+            Some(max_inodes.unwrap_or_else(default_pages)).filter(|inodes| *inodes != 0);
+        Ok(mount_options)
+    }
+}
+
+/// Parses a number with an optional suffix of `k`, `m`, `g` or `t`, which is case-insensitive.
+pub(in crate::fs) fn parse_number(s: &str) -> Result<usize> {
+    let (digits, shift) = match s.as_bytes().last().map(u8::to_ascii_lowercase) {
+        Some(b'k') => (&s[..s.len() - 1], 10),
+        Some(b'm') => (&s[..s.len() - 1], 20),
+        Some(b'g') => (&s[..s.len() - 1], 30),
+        Some(b't') => (&s[..s.len() - 1], 40),
+        _ => (s, 0),
+    };
+    digits
+        .parse::<usize>()
+        .ok()
+        .and_then(|number| number.checked_mul(1 << shift))
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid number"))
+}
+
+fn parse_id(s: &str) -> Result<u32> {
+    s.parse::<u32>()
+        .map_err(|_| Error::with_message(Errno::EINVAL, "invalid id"))
+}
+
+/// The pages and inodes that are used by a ramfs.
+#[derive(Debug)]
+pub(super) struct RamUsage {
+    max_pages: Option<usize>,
+    max_inodes: Option<usize>,
+    pages: AtomicUsize,
+    inodes: AtomicUsize,
+}
+
+impl RamUsage {
+    pub fn new(max_pages: Option<usize>, max_inodes: Option<usize>) -> Self {
+        Self {
+            max_pages,
+            max_inodes,
+            pages: AtomicUsize::new(0),
+            // The root directory is always charged.
+            inodes: AtomicUsize::new(1),
+        }
+    }
+
+    pub fn charge_pages(&self, nr_pages: usize) -> Result<()> {
+        charge(&self.pages, nr_pages, self.max_pages)
+    }
+
+    pub fn uncharge_pages(&self, nr_pages: usize) {
+        self.pages.fetch_sub(nr_pages, Ordering::Relaxed);
+    }
+
+    pub fn charge_inode(&self) -> Result<()> {
+        charge(&self.inodes, 1, self.max_inodes)
+    }
+
+    pub fn uncharge_inode(&self) {
+        self.inodes.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Fills the statistics of a limited file system.
+    pub fn fill_super_block(&self, sb: &mut SuperBlock) {
+        if let Some(max_pages) = self.max_pages {
+            let free_pages = max_pages.saturating_sub(self.pages.load(Ordering::Relaxed));
+            sb.blocks = max_pages;
+            sb.bfree = free_pages;
+            sb.bavail = free_pages;
+        }
+        if let Some(max_inodes) = self.max_inodes {
+            sb.files = max_inodes;
+            sb.ffree = max_inodes.saturating_sub(self.inodes.load(Ordering::Relaxed));
+        }
+    }
+}
+
+fn charge(counter: &AtomicUsize, nr: usize, max: Option<usize>) -> Result<()> {
+    let Some(max) = max else {
+        counter.fetch_add(nr, Ordering::Relaxed);
+        return Ok(());
+    };
+    counter
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+            used.checked_add(nr).filter(|new_used| *new_used <= max)
+        })
+        .map(|_| ())
+        .map_err(|_| Error::with_message(Errno::ENOSPC, "the tmpfs is full"))
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn parse_options() {
+        let options = TmpfsMountOptions::parse("size=64m,nr_inodes=1k,mode=755,uid=1000").unwrap();
+        assert_eq!(options.max_pages, Some((64 << 20) / BLOCK_SIZE));
+        assert_eq!(options.max_inodes, Some(1024));
+        assert_eq!(options.mode, InodeMode::from_bits_truncate(0o755));
+        assert_eq!(options.uid, Uid::new(1000));
+        assert_eq!(options.gid, Gid::new_root());
+
+        let options = TmpfsMountOptions::parse("size=0,nr_inodes=0").unwrap();
+        assert!(options.max_pages.is_none());
+        assert!(options.max_inodes.is_none());
+
+        assert!(TmpfsMountOptions::parse("size=1x").is_err());
+        assert!(TmpfsMountOptions::parse("huge=always").is_err());
+    }
+
+    #[ktest]
+    fn charge_within_limits() {
+        let usage = RamUsage::new(Some(2), Some(2));
+        usage.charge_pages(2).unwrap();
+        assert!(usage.charge_pages(1).is_err());
+        usage.uncharge_pages(1);
+        usage.charge_pages(1).unwrap();
+
+        usage.charge_inode().unwrap();
+        assert!(usage.charge_inode().is_err());
+        usage.uncharge_inode();
+        usage.charge_inode().unwrap();
+    }
+}
@@ -8,7 +8,7 @@ use spin::Once;
 
 use super::{
     fs_resolver::{FsPath, FsResolver},
-    path::MountNode,
+    path::{Dentry, MountNode},
     procfs::{self, ProcFS},
     ramfs::RamFS,
     utils::{FileSystem, InodeMode, InodeType},
@@ -137,3 +137,21 @@ pub fn init_root_mount() {
 pub fn root_mount() -> &'static Arc<MountNode> {
     ROOT_MOUNT.get().unwrap()
 }
+
+static ROOT_DENTRY: Once<Dentry> = Once::new();
+
+/// Changes the root directory of the processes that are created afterwards.
+///
+/// It is done at most once during boot, e.g., after the network root file system is mounted.
+pub fn switch_root(dentry: Dentry) {
+    ROOT_DENTRY.call_once(|| dentry);
+}
+
+/// Returns the root directory of the processes, which is the root of the initramfs unless
+/// it is switched.
+pub fn root_dentry() -> Dentry {
+    match ROOT_DENTRY.get() {
+        Some(dentry) => dentry.clone(),
+        None => Dentry::new_fs_root(root_mount().clone()),
+    }
+}
@@ -0,0 +1,287 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::fmt::Write;
+
+use ostd::trace::{tracepoints, Tracepoint};
+
+use super::read_bool;
+use crate::{
+    fs::{
+        procfs::template::{DirOps, FileOps, ProcDir, ProcDirBuilder, ProcFileBuilder},
+        utils::{DirEntryVecExt, Inode, InodeMode},
+    },
+    prelude::*,
+};
+
+/// Represents the inode at `available_events`.
+pub struct AvailableEventsFileOps;
+
+impl AvailableEventsFileOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self).parent(parent).build().unwrap()
+    }
+}
+
+impl FileOps for AvailableEventsFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let mut output = String::new();
+        for tracepoint in tracepoints() {
+            let _ = writeln!(output, "{}:{}", tracepoint.system(), tracepoint.name());
+        }
+        Ok(output.into_bytes())
+    }
+}
+
+/// Represents the inode at `set_event`.
+///
+/// Unlike Linux, opening the file with `O_TRUNC` does not disable the enabled tracepoints,
+/// since the truncation of the files are ignored. Instead, writing an empty line disables all
+/// the tracepoints.
+pub struct SetEventFileOps;
+
+impl SetEventFileOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        let inode = ProcFileBuilder::new(Self).parent(parent).build().unwrap();
+        inode
+            .set_mode(InodeMode::from_bits_truncate(0o644))
+            .unwrap();
+        inode
+    }
+}
+
+impl FileOps for SetEventFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let mut output = String::new();
+        for tracepoint in tracepoints().iter().filter(|tp| tp.is_enabled()) {
+            let _ = writeln!(output, "{}:{}", tracepoint.system(), tracepoint.name());
+        }
+        Ok(output.into_bytes())
+    }
+
+    fn write_at(&self, _offset: usize, reader: &mut VmReader) -> Result<usize> {
+        let len = reader.remain();
+        let mut buf = vec![0u8; len];
+        reader.read_fallible(&mut VmWriter::from(buf.as_mut_slice()))?;
+
+        let input = core::str::from_utf8(&buf)
+            .map_err(|_| Error::with_message(Errno::EINVAL, "the events are not valid UTF-8"))?;
+        if input.trim().is_empty() {
+            EventScope::All.set_enabled(false);
+            return Ok(len);
+        }
+
+        for event in input.split_whitespace() {
+            let (is_enabled, event) = match event.strip_prefix('!') {
+                Some(event) => (false, event),
+                None => (true, event),
+            };
+            // An event without the system (e.g., `sched_switch`) matches all the systems.
+            let (system, name) = event.split_once(':').unwrap_or(("*", event));
+
+            let mut is_found = false;
+            for tracepoint in tracepoints().into_iter().filter(|tp| {
+                (system == "*" || system == tp.system()) && (name == "*" || name == tp.name())
+            }) {
+                tracepoint.set_enabled(is_enabled);
+                is_found = true;
+            }
+            if !is_found {
+                return_errno_with_message!(Errno::EINVAL, "the event does not exist");
+            }
+        }
+
+        Ok(len)
+    }
+}
+
+/// Represents the inode at `events`.
+pub struct EventsDirOps;
+
+impl EventsDirOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcDirBuilder::new(Self).parent(parent).build().unwrap()
+    }
+}
+
+impl DirOps for EventsDirOps {
+    fn lookup_child(&self, this_ptr: Weak<dyn Inode>, name: &str) -> Result<Arc<dyn Inode>> {
+        if name == "enable" {
+            return Ok(EnableFileOps::new_inode(EventScope::All, this_ptr));
+        }
+
+        let Some(tracepoint) = tracepoints().into_iter().find(|tp| tp.system() == name) else {
+            return_errno!(Errno::ENOENT);
+        };
+        Ok(SystemDirOps::new_inode(tracepoint.system(), this_ptr))
+    }
+
+    fn populate_children(&self, this_ptr: Weak<dyn Inode>) {
+        let this = {
+            let this = this_ptr.upgrade().unwrap();
+            this.downcast_ref::<ProcDir<EventsDirOps>>().unwrap().this()
+        };
+        let mut cached_children = this.cached_children().write();
+        cached_children.put_entry_if_not_found("enable", || {
+            EnableFileOps::new_inode(EventScope::All, this_ptr.clone())
+        });
+        for tracepoint in tracepoints() {
+            let system = tracepoint.system();
+            cached_children.put_entry_if_not_found(system, || {
+                SystemDirOps::new_inode(system, this_ptr.clone())
+            });
+        }
+    }
+}
+
+/// Represents the inode at `events/<system>`.
+struct SystemDirOps {
+    system: &'static str,
+}
+
+impl SystemDirOps {
+    fn new_inode(system: &'static str, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcDirBuilder::new(Self { system })
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl DirOps for SystemDirOps {
+    fn lookup_child(&self, this_ptr: Weak<dyn Inode>, name: &str) -> Result<Arc<dyn Inode>> {
+        if name == "enable" {
+            return Ok(EnableFileOps::new_inode(
+                EventScope::System(self.system),
+                this_ptr,
+            ));
+        }
+
+        let Some(tracepoint) = tracepoints()
+            .into_iter()
+            .find(|tp| tp.system() == self.system && tp.name() == name)
+        else {
+            return_errno!(Errno::ENOENT);
+        };
+        Ok(EventDirOps::new_inode(tracepoint, this_ptr))
+    }
+
+    fn populate_children(&self, this_ptr: Weak<dyn Inode>) {
+        let this = {
+            let this = this_ptr.upgrade().unwrap();
+            this.downcast_ref::<ProcDir<SystemDirOps>>().unwrap().this()
+        };
+        let mut cached_children = this.cached_children().write();
+        cached_children.put_entry_if_not_found("enable", || {
+            EnableFileOps::new_inode(EventScope::System(self.system), this_ptr.clone())
+        });
+        for tracepoint in tracepoints()
+            .into_iter()
+            .filter(|tp| tp.system() == self.system)
+        {
+            cached_children.put_entry_if_not_found(tracepoint.name(), || {
+                EventDirOps::new_inode(tracepoint, this_ptr.clone())
+            });
+        }
+    }
+}
+
+/// Represents the inode at `events/<system>/<event>`.
+struct EventDirOps {
+    tracepoint: &'static Tracepoint,
+}
+
+impl EventDirOps {
+    fn new_inode(tracepoint: &'static Tracepoint, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcDirBuilder::new(Self { tracepoint })
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl DirOps for EventDirOps {
+    fn lookup_child(&self, this_ptr: Weak<dyn Inode>, name: &str) -> Result<Arc<dyn Inode>> {
+        let inode = match name {
+            "enable" => EnableFileOps::new_inode(EventScope::Event(self.tracepoint), this_ptr),
+            _ => return_errno!(Errno::ENOENT),
+        };
+        Ok(inode)
+    }
+
+    fn populate_children(&self, this_ptr: Weak<dyn Inode>) {
+        let this = {
+            let this = this_ptr.upgrade().unwrap();
+            this.downcast_ref::<ProcDir<EventDirOps>>().unwrap().this()
+        };
+        let mut cached_children = this.cached_children().write();
+        cached_children.put_entry_if_not_found("enable", || {
+            EnableFileOps::new_inode(EventScope::Event(self.tracepoint), this_ptr.clone())
+        });
+    }
+}
+
+/// The tracepoints controlled by an `enable` file.
+#[derive(Clone, Copy)]
+enum EventScope {
+    All,
+    System(&'static str),
+    Event(&'static Tracepoint),
+}
+
+impl EventScope {
+    fn tracepoints(&self) -> Vec<&'static Tracepoint> {
+        tracepoints()
+            .into_iter()
+            .filter(|tp| match self {
+                Self::All => true,
+                Self::System(system) => tp.system() == *system,
+                Self::Event(tracepoint) => core::ptr::eq(*tp, *tracepoint),
+            })
+            .collect()
+    }
+
+    fn set_enabled(&self, is_enabled: bool) {
+        for tracepoint in self.tracepoints() {
+            tracepoint.set_enabled(is_enabled);
+        }
+    }
+}
+
+/// Represents the inode at `enable` in the directories of the events.
+struct EnableFileOps {
+    scope: EventScope,
+}
+
+impl EnableFileOps {
+    fn new_inode(scope: EventScope, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        let inode = ProcFileBuilder::new(Self { scope })
+            .parent(parent)
+            .build()
+            .unwrap();
+        inode
+            .set_mode(InodeMode::from_bits_truncate(0o644))
+            .unwrap();
+        inode
+    }
+}
+
+impl FileOps for EnableFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let tracepoints = self.scope.tracepoints();
+        // Like Linux, "X" means that only some of the tracepoints are enabled.
+        let output = if tracepoints.iter().all(|tp| tp.is_enabled()) {
+            "1\n"
+        } else if tracepoints.iter().all(|tp| !tp.is_enabled()) {
+            "0\n"
+        } else {
+            "X\n"
+        };
+        Ok(output.as_bytes().to_vec())
+    }
+
+    fn write_at(&self, _offset: usize, reader: &mut VmReader) -> Result<usize> {
+        let (is_enabled, len) = read_bool(reader)?;
+        self.scope.set_enabled(is_enabled);
+        Ok(len)
+    }
+}
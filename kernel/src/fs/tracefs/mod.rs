@@ -0,0 +1,170 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The tracefs.
+//!
+//! The filesystem controls the tracepoints and reads the trace buffer (see [`ostd::trace`]).
+//! It is usually mounted at "/sys/kernel/tracing", and contains the following files:
+//!  - `trace`: the events in the trace buffer, which is cleared by writing to the file;
+//!  - `trace_pipe`: the same as `trace`, but the reads consume the events, and block if there
+//!    are no events;
+//!  - `tracing_on`: whether the events are being recorded;
+//!  - `available_events`: the tracepoints in the form of `<system>:<event>`;
+//!  - `set_event`: the enabled tracepoints, which are enabled by writing `<system>:<event>`,
+//!    and disabled by writing `!<system>:<event>`;
+//!  - `buffer_size_kb`: the size of the trace buffer of each CPU;
+//!  - `events/enable`, `events/<system>/enable` and `events/<system>/<event>/enable`: whether
+//!    the tracepoints in the scope are enabled.
+//!
+//! Only the events of the tracepoints are traced, i.e., the current tracer is always `nop`.
+//! The function tracers of Linux are not supported, since they rely on the instrumentation of
+//! all functions by the compiler.
+//!
+//! Reference: <https://www.kernel.org/doc/html/latest/trace/ftrace.html>
+
+use alloc::format;
+use core::mem::size_of;
+
+use ostd::trace::TraceEvent;
+use spin::Once;
+
+use self::{
+    events::{AvailableEventsFileOps, EventsDirOps, SetEventFileOps},
+    trace::{TraceFileOps, TracePipeFileOps},
+};
+use crate::{
+    fs::{
+        procfs::{
+            template::{DirOps, FileOps, ProcDir, ProcDirBuilder, ProcFileBuilder},
+            ProcFS, PROC_ROOT_INO,
+        },
+        utils::{DirEntryVecExt, Inode, InodeMode},
+    },
+    prelude::*,
+};
+
+mod events;
+mod trace;
+
+const TRACEFS_MAGIC: u64 = 0x74726163;
+
+/// Returns the instance of the tracefs.
+///
+/// Like Linux, there is only one instance of the filesystem, so all the mounts of the
+/// filesystem share the same files.
+pub fn singleton() -> &'static Arc<ProcFS> {
+    static TRACEFS: Once<Arc<ProcFS>> = Once::new();
+
+    TRACEFS.call_once(|| ProcFS::new_with_root(TRACEFS_MAGIC, RootDirOps::new_inode))
+}
+
+/// Represents the root inode of the tracefs.
+struct RootDirOps;
+
+impl RootDirOps {
+    fn new_inode(fs: Weak<ProcFS>) -> Arc<dyn Inode> {
+        ProcDirBuilder::new(Self)
+            .fs(fs)
+            .ino(PROC_ROOT_INO)
+            .build()
+            .unwrap()
+    }
+}
+
+impl DirOps for RootDirOps {
+    fn lookup_child(&self, this_ptr: Weak<dyn Inode>, name: &str) -> Result<Arc<dyn Inode>> {
+        let inode = match name {
+            "available_events" => AvailableEventsFileOps::new_inode(this_ptr.clone()),
+            "buffer_size_kb" => BufferSizeFileOps::new_inode(this_ptr.clone()),
+            "events" => EventsDirOps::new_inode(this_ptr.clone()),
+            "set_event" => SetEventFileOps::new_inode(this_ptr.clone()),
+            "trace" => TraceFileOps::new_inode(this_ptr.clone()),
+            "trace_pipe" => TracePipeFileOps::new_inode(this_ptr.clone()),
+            "tracing_on" => TracingOnFileOps::new_inode(this_ptr.clone()),
+            _ => return_errno!(Errno::ENOENT),
+        };
+        Ok(inode)
+    }
+
+    fn populate_children(&self, this_ptr: Weak<dyn Inode>) {
+        let this = {
+            let this = this_ptr.upgrade().unwrap();
+            this.downcast_ref::<ProcDir<RootDirOps>>().unwrap().this()
+        };
+        let mut cached_children = this.cached_children().write();
+        cached_children.put_entry_if_not_found("available_events", || {
+            AvailableEventsFileOps::new_inode(this_ptr.clone())
+        });
+        cached_children.put_entry_if_not_found("buffer_size_kb", || {
+            BufferSizeFileOps::new_inode(this_ptr.clone())
+        });
+        cached_children
+            .put_entry_if_not_found("events", || EventsDirOps::new_inode(this_ptr.clone()));
+        cached_children
+            .put_entry_if_not_found("set_event", || SetEventFileOps::new_inode(this_ptr.clone()));
+        cached_children
+            .put_entry_if_not_found("trace", || TraceFileOps::new_inode(this_ptr.clone()));
+        cached_children.put_entry_if_not_found("trace_pipe", || {
+            TracePipeFileOps::new_inode(this_ptr.clone())
+        });
+        cached_children.put_entry_if_not_found("tracing_on", || {
+            TracingOnFileOps::new_inode(this_ptr.clone())
+        });
+    }
+}
+
+/// Represents the inode at `tracing_on`.
+struct TracingOnFileOps;
+
+impl TracingOnFileOps {
+    fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        let inode = ProcFileBuilder::new(Self).parent(parent).build().unwrap();
+        inode
+            .set_mode(InodeMode::from_bits_truncate(0o644))
+            .unwrap();
+        inode
+    }
+}
+
+impl FileOps for TracingOnFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let output = format!("{}\n", ostd::trace::is_tracing_on() as u8);
+        Ok(output.into_bytes())
+    }
+
+    fn write_at(&self, _offset: usize, reader: &mut VmReader) -> Result<usize> {
+        let (is_on, len) = read_bool(reader)?;
+        ostd::trace::set_tracing_on(is_on);
+        Ok(len)
+    }
+}
+
+/// Represents the inode at `buffer_size_kb`.
+struct BufferSizeFileOps;
+
+impl BufferSizeFileOps {
+    fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self).parent(parent).build().unwrap()
+    }
+}
+
+impl FileOps for BufferSizeFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let size = ostd::trace::buffer_entries() * size_of::<TraceEvent>();
+        let output = format!("{}\n", size / 1024);
+        Ok(output.into_bytes())
+    }
+}
+
+/// Reads a written `0` or `1`, and returns the boolean value and the length of the input.
+fn read_bool(reader: &mut VmReader) -> Result<(bool, usize)> {
+    let len = reader.remain();
+    let mut buf = vec![0u8; len];
+    reader.read_fallible(&mut VmWriter::from(buf.as_mut_slice()))?;
+
+    let value = match core::str::from_utf8(&buf).map(str::trim) {
+        Ok("0") => false,
+        Ok("1") => true,
+        _ => return_errno_with_message!(Errno::EINVAL, "the value is not 0 or 1"),
+    };
+    Ok((value, len))
+}
@@ -0,0 +1,134 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::fmt::Write;
+
+use ostd::{cpu::all_cpus, trace::TraceEvent};
+use spin::Once;
+
+use crate::{
+    events::IoEvents,
+    fs::{
+        procfs::template::{FileOps, ProcFileBuilder},
+        utils::{Inode, InodeMode},
+    },
+    prelude::*,
+    process::signal::{PollHandle, Pollable, Pollee},
+};
+
+/// Represents the inode at `trace`.
+pub struct TraceFileOps;
+
+impl TraceFileOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        let inode = ProcFileBuilder::new(Self).parent(parent).build().unwrap();
+        inode
+            .set_mode(InodeMode::from_bits_truncate(0o644))
+            .unwrap();
+        inode
+    }
+}
+
+impl FileOps for TraceFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let events = ostd::trace::read_events();
+        let nr_overruns: u64 = all_cpus().map(ostd::trace::nr_overruns).sum();
+
+        let mut output = String::new();
+        let _ = writeln!(output, "# tracer: nop");
+        let _ = writeln!(output, "#");
+        let _ = writeln!(
+            output,
+            "# entries-in-buffer/entries-written: {}/{}",
+            events.len(),
+            events.len() as u64 + nr_overruns
+        );
+        let _ = writeln!(output, "#");
+        let _ = writeln!(output, "#  CPU     TIMESTAMP  FUNCTION");
+        let _ = writeln!(output, "#   |         |         |");
+        for event in events.iter() {
+            format_event(event, &mut output);
+        }
+        Ok(output.into_bytes())
+    }
+
+    fn write_at(&self, _offset: usize, reader: &mut VmReader) -> Result<usize> {
+        // Like Linux, writing anything clears the trace buffer.
+        let len = reader.remain();
+        reader.skip(len);
+        ostd::trace::clear();
+        Ok(len)
+    }
+}
+
+/// Represents the inode at `trace_pipe`.
+pub struct TracePipeFileOps;
+
+impl TracePipeFileOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        POLLEE.call_once(|| {
+            ostd::trace::register_notifier(|| POLLEE.get().unwrap().notify(IoEvents::IN));
+            Pollee::new()
+        });
+        ProcFileBuilder::new(Self).parent(parent).build().unwrap()
+    }
+}
+
+static POLLEE: Once<Pollee> = Once::new();
+
+/// The text of the consumed events that has not been read yet.
+static PENDING_TEXT: Mutex<String> = Mutex::new(String::new());
+
+impl FileOps for TracePipeFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        unreachable!("the data of `trace_pipe` is read by `read_at`")
+    }
+
+    fn read_at(&self, _offset: usize, writer: &mut VmWriter) -> Result<usize> {
+        let mut pending_text = PENDING_TEXT.lock();
+        if pending_text.is_empty() {
+            let event = TraceBuffer.wait_events(IoEvents::IN, None, || {
+                ostd::trace::consume_event()
+                    .ok_or_else(|| Error::with_message(Errno::EAGAIN, "there are no events"))
+            })?;
+            format_event(&event, &mut pending_text);
+        }
+        // Read as many events as possible at once.
+        while pending_text.len() < writer.avail() {
+            let Some(event) = ostd::trace::consume_event() else {
+                break;
+            };
+            format_event(&event, &mut pending_text);
+        }
+
+        let len = pending_text.len().min(writer.avail());
+        writer.write_fallible(&mut VmReader::from(&pending_text.as_bytes()[..len]))?;
+        pending_text.drain(..len);
+        Ok(len)
+    }
+}
+
+/// The trace buffer, which becomes readable when new events are recorded.
+struct TraceBuffer;
+
+impl Pollable for TraceBuffer {
+    fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
+        if let Some(poller) = poller {
+            POLLEE.get().unwrap().register_poller(poller, mask);
+        }
+        IoEvents::empty()
+    }
+}
+
+fn format_event(event: &TraceEvent, output: &mut String) {
+    let timestamp = event.timestamp();
+    let _ = write!(
+        output,
+        "  [{:03}] {:>6}.{:06}: {}: ",
+        event.cpu().as_usize(),
+        timestamp.as_secs(),
+        timestamp.subsec_micros(),
+        event.tracepoint().name()
+    );
+    let _ = event.format_args(output);
+    output.push('\n');
+}
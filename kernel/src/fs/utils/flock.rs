@@ -113,22 +113,25 @@ impl FlockList {
     /// If a conflicting lock exists:
     /// - If waker is not `None`, it is added to the conflicting lock's waitqueue, and the function returns `EAGAIN`.
     /// - If waker is `None`, the function returns `EAGAIN`.
+    ///
+    /// Like Linux, converting a lock of the same owner is not atomic. The existing lock
+    /// is removed first, so two owners upgrading their shared locks do not deadlock.
     fn try_set_lock(&self, req_lock: &FlockItem, waker: Option<&Arc<Waker>>) -> Result<()> {
         let mut list = self.inner.lock();
+        if let Some(idx) = list.iter().position(|l| req_lock.same_owner_with(l)) {
+            if list[idx].lock.type_ == req_lock.lock.type_ {
+                return Ok(());
+            }
+            // Dropping the lock wakes the threads that are waiting for it.
+            list.remove(idx);
+        }
         if let Some(conflict_lock) = list.iter().find(|l| req_lock.conflict_with(l)) {
             if let Some(waker) = waker {
                 conflict_lock.waitqueue.enqueue(waker.clone());
             }
             return_errno_with_message!(Errno::EAGAIN, "the file is locked");
         } else {
-            match list.iter().position(|l| req_lock.same_owner_with(l)) {
-                Some(idx) => {
-                    list[idx] = req_lock.clone();
-                }
-                None => {
-                    list.push(req_lock.clone());
-                }
-            }
+            list.push(req_lock.clone());
             Ok(())
         }
     }
@@ -9,12 +9,15 @@ use core2::io::{Error as IoError, ErrorKind as IoErrorKind, Result as IoResult,
 use ostd::task::Task;
 
 use super::{
-    AccessMode, DirentVisitor, FallocMode, FileSystem, IoctlCmd, XattrName, XattrNamespace,
-    XattrSetFlags,
+    AccessMode, DirentVisitor, FallocMode, FileSystem, FsVerity, FsVerityParams, IoctlCmd,
+    XattrName, XattrNamespace, XattrSetFlags,
 };
 use crate::{
     events::IoEvents,
-    fs::device::{Device, DeviceType},
+    fs::{
+        device::{Device, DeviceType},
+        inode_handle::FileIo,
+    },
     prelude::*,
     process::{posix_thread::AsPosixThread, signal::PollHandle, Gid, Uid},
     time::clocks::RealTimeCoarseClock,
@@ -210,6 +213,8 @@ pub struct Metadata {
     pub atime: Duration,
     pub mtime: Duration,
     pub ctime: Duration,
+    /// The creation time, or `None` if the file system does not record it.
+    pub btime: Option<Duration>,
     pub type_: InodeType,
     pub mode: InodeMode,
     pub nlinks: usize,
@@ -230,6 +235,7 @@ impl Metadata {
             atime: now,
             mtime: now,
             ctime: now,
+            btime: None,
             type_: InodeType::Dir,
             mode,
             nlinks: 2,
@@ -250,6 +256,7 @@ impl Metadata {
             atime: now,
             mtime: now,
             ctime: now,
+            btime: None,
             type_: InodeType::File,
             mode,
             nlinks: 1,
@@ -270,6 +277,7 @@ impl Metadata {
             atime: now,
             mtime: now,
             ctime: now,
+            btime: None,
             type_: InodeType::SymLink,
             mode,
             nlinks: 1,
@@ -289,6 +297,7 @@ impl Metadata {
             atime: now,
             mtime: now,
             ctime: now,
+            btime: None,
             type_: InodeType::from(device.type_()),
             mode,
             nlinks: 1,
@@ -309,6 +318,7 @@ impl Metadata {
             atime: now,
             mtime: now,
             ctime: now,
+            btime: None,
             type_: InodeType::Socket,
             mode,
             nlinks: 1,
@@ -413,6 +423,14 @@ pub trait Inode: Any + Sync + Send {
         None
     }
 
+    /// Opens the inode that is not a device.
+    ///
+    /// Like [`Device::open`], the inode can return a [`FileIo`] to serve the opened file, if
+    /// each opened file needs its own states.
+    fn open(&self) -> Result<Option<Arc<dyn FileIo>>> {
+        Ok(None)
+    }
+
     fn readdir_at(&self, offset: usize, visitor: &mut dyn DirentVisitor) -> Result<usize> {
         Err(Error::new(Errno::ENOTDIR))
     }
@@ -437,6 +455,15 @@ pub trait Inode: Any + Sync + Send {
         Err(Error::new(Errno::ENOTDIR))
     }
 
+    /// Exchanges the file of `old_name` in this directory and that of `new_name`
+    /// in the `target` directory atomically. Both files must exist.
+    fn exchange(&self, old_name: &str, target: &Arc<dyn Inode>, new_name: &str) -> Result<()> {
+        Err(Error::with_message(
+            Errno::EINVAL,
+            "the file system does not support exchanging files",
+        ))
+    }
+
     fn read_link(&self) -> Result<String> {
         Err(Error::new(Errno::EISDIR))
     }
@@ -468,6 +495,9 @@ pub trait Inode: Any + Sync + Send {
         events & mask
     }
 
+    /// Called when a file descriptor that refers to the inode is closed by the current process.
+    fn on_fd_close(&self) {}
+
     fn fs(&self) -> Arc<dyn FileSystem>;
 
     /// Returns whether a VFS dentry for this inode should be put into the dentry cache.
@@ -491,6 +521,14 @@ pub trait Inode: Any + Sync + Send {
         true
     }
 
+    /// Returns whether the inode is a "magic link", e.g., `/proc/[pid]/fd/N`.
+    ///
+    /// A magic link refers to an object in the kernel rather than a path, so the path
+    /// it reads is only a hint. The lookups with `RESOLVE_NO_MAGICLINKS` refuse to follow it.
+    fn is_magic_link(&self) -> bool {
+        false
+    }
+
     fn is_seekable(&self) -> bool {
         true
     }
@@ -521,6 +559,24 @@ pub trait Inode: Any + Sync + Send {
         Err(Error::new(Errno::EOPNOTSUPP))
     }
 
+    /// Returns the fs-verity state of the file, or `None` if fs-verity is not enabled.
+    fn verity(&self) -> Result<Option<Arc<FsVerity>>> {
+        Ok(None)
+    }
+
+    /// Enables fs-verity on the file, which makes the file read-only.
+    ///
+    /// The file system should persist the descriptor of the file, and verify the data read from
+    /// the storage against the Merkle tree afterwards (see [`PageCacheBackend::verify_page`]).
+    ///
+    /// [`PageCacheBackend::verify_page`]: super::PageCacheBackend::verify_page
+    fn enable_verity(&self, params: &FsVerityParams) -> Result<()> {
+        return_errno_with_message!(
+            Errno::EOPNOTSUPP,
+            "the file system does not support fs-verity"
+        );
+    }
+
     /// Used to check for read/write/execute permissions on a file.
     ///
     /// Similar to Linux, using "fsuid" here allows setting filesystem permissions
@@ -12,21 +12,31 @@ pub enum IoctlCmd {
     TCSETSW = 0x5403,
     /// Drain the output buffer, and discard pending input, and set attributes
     TCSETSF = 0x5404,
+    /// Wait until the output is transmitted (and send a break if the argument is zero)
+    TCSBRK = 0x5409,
+    /// Discard the data in the input or output queue
+    TCFLSH = 0x540b,
     /// Make the given terminal the controlling terminal of the calling process.
     TIOCSCTTY = 0x540e,
     /// Get the process group ID of the foreground process group on this terminal
     TIOCGPGRP = 0x540f,
     /// Set the foreground process group ID of this terminal.
     TIOCSPGRP = 0x5410,
+    /// Get the number of bytes in the output buffer.
+    TIOCOUTQ = 0x5411,
     /// Get the number of bytes in the input buffer.
     FIONREAD = 0x541B,
     /// Set window size
     TIOCGWINSZ = 0x5413,
     TIOCSWINSZ = 0x5414,
+    /// Enable or disable the packet mode of a pty master
+    TIOCPKT = 0x5420,
     /// Enable or disable non-blocking I/O mode.
     FIONBIO = 0x5421,
     /// the calling process gives up this controlling terminal
     TIOCNOTTY = 0x5422,
+    /// Get the session ID of the calling process's controlling terminal
+    TIOCGSID = 0x5429,
     /// Clear the close on exec flag on a file descriptor
     FIONCLEX = 0x5450,
     /// Set the close on exec flag on a file descriptor
@@ -37,8 +47,154 @@ pub enum IoctlCmd {
     TIOCGPTN = 0x80045430,
     /// Lock/unlock Pty
     TIOCSPTLCK = 0x40045431,
+    /// Get whether the packet mode of a pty master is enabled
+    TIOCGPKT = 0x80045438,
+    /// Get whether Pty is locked
+    TIOCGPTLCK = 0x80045439,
     /// Safely open the slave
-    TIOCGPTPEER = 0x40045441,
+    TIOCGPTPEER = 0x5441,
     /// Get tdx report using TDCALL
     TDXGETREPORT = 0xc4405401,
+    /// Get the version of the device mapper interface
+    DM_VERSION = 0xc138fd00,
+    /// Remove all mapped devices
+    DM_REMOVE_ALL = 0xc138fd01,
+    /// List the mapped devices
+    DM_LIST_DEVICES = 0xc138fd02,
+    /// Create a mapped device
+    DM_DEV_CREATE = 0xc138fd03,
+    /// Remove a mapped device
+    DM_DEV_REMOVE = 0xc138fd04,
+    /// Suspend or resume a mapped device
+    DM_DEV_SUSPEND = 0xc138fd06,
+    /// Get the status of a mapped device
+    DM_DEV_STATUS = 0xc138fd07,
+    /// Load a table into a mapped device as the inactive table
+    DM_TABLE_LOAD = 0xc138fd09,
+    /// Clear the inactive table of a mapped device
+    DM_TABLE_CLEAR = 0xc138fd0a,
+    /// Get the table or the target status of a mapped device
+    DM_TABLE_STATUS = 0xc138fd0c,
+    /// List the supported target types
+    DM_LIST_VERSIONS = 0xc138fd0d,
+    /// Bind a loop device to a file
+    LOOP_SET_FD = 0x4c00,
+    /// Unbind a loop device from its file
+    LOOP_CLR_FD = 0x4c01,
+    /// Set the status of a loop device
+    LOOP_SET_STATUS64 = 0x4c04,
+    /// Get the status of a loop device
+    LOOP_GET_STATUS64 = 0x4c05,
+    /// Update the size of a loop device after its file is resized
+    LOOP_SET_CAPACITY = 0x4c07,
+    /// Set the logical block size of a loop device
+    LOOP_SET_BLOCK_SIZE = 0x4c09,
+    /// Bind a loop device to a file and set its status at once
+    LOOP_CONFIGURE = 0x4c0a,
+    /// Add a loop device
+    LOOP_CTL_ADD = 0x4c80,
+    /// Remove a loop device
+    LOOP_CTL_REMOVE = 0x4c81,
+    /// Get or add a loop device that is not bound to any file
+    LOOP_CTL_GET_FREE = 0x4c82,
+    /// Get the size of a block device in bytes
+    BLKGETSIZE64 = 0x80081272,
+    /// Attach to a TUN/TAP device, creating it if it does not exist
+    TUNSETIFF = 0x400454ca,
+    /// Set whether a TUN/TAP device is kept after its file is closed
+    TUNSETPERSIST = 0x400454cb,
+    /// Get the flags supported by TUN/TAP devices
+    TUNGETFEATURES = 0x800454cf,
+    /// Set the offloads that the user space can handle for a TUN/TAP device
+    TUNSETOFFLOAD = 0x400454d0,
+    /// Get the name and the flags of a TUN/TAP device
+    TUNGETIFF = 0x800454d2,
+    /// Get the size of the virtio network header of a TUN/TAP device
+    TUNGETVNETHDRSZ = 0x800454d7,
+    /// Set the size of the virtio network header of a TUN/TAP device
+    TUNSETVNETHDRSZ = 0x400454d8,
+    /// Enable fs-verity on a file
+    FS_IOC_ENABLE_VERITY = 0x40806685,
+    /// Get the digest of a file with fs-verity enabled
+    FS_IOC_MEASURE_VERITY = 0xc0046686,
+    /// Read the fs-verity metadata of a file
+    FS_IOC_READ_VERITY_METADATA = 0xc0286687,
+    /// Enable a perf event
+    PERF_EVENT_IOC_ENABLE = 0x2400,
+    /// Disable a perf event
+    PERF_EVENT_IOC_DISABLE = 0x2401,
+    /// Reset the count of a perf event
+    PERF_EVENT_IOC_RESET = 0x2403,
+    /// Set the sampling period of a perf event
+    PERF_EVENT_IOC_PERIOD = 0x40082404,
+    /// Get the ID of a perf event
+    PERF_EVENT_IOC_ID = 0x80082407,
+    /// Get the information of a watchdog
+    WDIOC_GETSUPPORT = 0x80285700,
+    /// Get the status of a watchdog
+    WDIOC_GETSTATUS = 0x80045701,
+    /// Get the status of a watchdog at the last reboot
+    WDIOC_GETBOOTSTATUS = 0x80045702,
+    /// Get the temperature measured by a watchdog
+    WDIOC_GETTEMP = 0x80045703,
+    /// Start or stop a watchdog
+    WDIOC_SETOPTIONS = 0x80045704,
+    /// Keep a watchdog alive
+    WDIOC_KEEPALIVE = 0x80045705,
+    /// Set the timeout of a watchdog
+    WDIOC_SETTIMEOUT = 0xc0045706,
+    /// Get the timeout of a watchdog
+    WDIOC_GETTIMEOUT = 0x80045707,
+    /// Set the pretimeout of a watchdog
+    WDIOC_SETPRETIMEOUT = 0xc0045708,
+    /// Get the pretimeout of a watchdog
+    WDIOC_GETPRETIMEOUT = 0x80045709,
+    /// Get the seconds left before a watchdog resets the system
+    WDIOC_GETTIMELEFT = 0x8004570a,
+    /// Set the address of the I2C device to talk to
+    I2C_SLAVE = 0x0703,
+    /// Set the address of the I2C device even if it is used by a driver
+    I2C_SLAVE_FORCE = 0x0706,
+    /// Use 10-bit addresses for I2C devices
+    I2C_TENBIT = 0x0704,
+    /// Get the functionality of an I2C adapter
+    I2C_FUNCS = 0x0705,
+    /// Do a combined I2C transfer
+    I2C_RDWR = 0x0707,
+    /// Do an SMBus transfer
+    I2C_SMBUS = 0x0720,
+    /// Get the version of the KVM API
+    KVM_GET_API_VERSION = 0xae00,
+    /// Create a VM
+    KVM_CREATE_VM = 0xae01,
+    /// Check whether a KVM capability is supported
+    KVM_CHECK_EXTENSION = 0xae03,
+    /// Get the size of the shared `struct kvm_run` of a vCPU
+    KVM_GET_VCPU_MMAP_SIZE = 0xae04,
+    /// Create a vCPU in a VM
+    KVM_CREATE_VCPU = 0xae41,
+    /// Set a memory region of a VM
+    KVM_SET_USER_MEMORY_REGION = 0x4020ae46,
+    /// Run a vCPU
+    KVM_RUN = 0xae80,
+    /// Set or clear the external interrupt of a vCPU
+    KVM_INTERRUPT = 0x4004ae86,
+    /// Send an MSI to a VM
+    KVM_SIGNAL_MSI = 0x4020aea5,
+    /// Get the multiprocessing state of a vCPU
+    KVM_GET_MP_STATE = 0x8004ae98,
+    /// Set the multiprocessing state of a vCPU
+    KVM_SET_MP_STATE = 0x4004ae99,
+    /// Get a register of a vCPU
+    KVM_GET_ONE_REG = 0x4010aeab,
+    /// Set a register of a vCPU
+    KVM_SET_ONE_REG = 0x4010aeac,
+    /// Create a device in a VM
+    KVM_CREATE_DEVICE = 0xc00caee0,
+    /// Set an attribute of a KVM device
+    KVM_SET_DEVICE_ATTR = 0x4018aee1,
+    /// Get an attribute of a KVM device
+    KVM_GET_DEVICE_ATTR = 0x4018aee2,
+    /// Check whether a KVM device has an attribute
+    KVM_HAS_DEVICE_ATTR = 0x4018aee3,
 }
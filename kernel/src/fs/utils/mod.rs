@@ -19,6 +19,10 @@ pub use range_lock::{
     FileRange, RangeLockItem, RangeLockItemBuilder, RangeLockList, RangeLockType, OFFSET_MAX,
 };
 pub use status_flags::StatusFlags;
+pub use verity::{
+    ioctl_enable_verity, ioctl_measure_verity, ioctl_read_verity_metadata, FsVerity,
+    FsVerityDescriptor, FsVerityParams,
+};
 pub use xattr::{
     XattrName, XattrNamespace, XattrSetFlags, XATTR_LIST_MAX_LEN, XATTR_NAME_MAX_LEN,
     XATTR_VALUE_MAX_LEN,
@@ -39,6 +43,7 @@ mod page_cache;
 mod random_test;
 mod range_lock;
 mod status_flags;
+mod verity;
 mod xattr;
 
 use core::{
@@ -250,14 +250,21 @@ impl ReadaheadState {
     pub fn wait_for_prev_readahead(
         &mut self,
         pages: &mut MutexGuard<LruCache<usize, CachePage>>,
+        backend: &dyn PageCacheBackend,
     ) -> Result<()> {
         if matches!(self.waiter.wait(), Some(BioStatus::Complete)) {
             let Some(window) = &self.ra_window else {
                 return_errno!(Errno::EINVAL)
             };
             for idx in window.readahead_range() {
-                if let Some(page) = pages.get_mut(&idx) {
+                let Some(page) = pages.get_mut(&idx) else {
+                    continue;
+                };
+                // The pages that fail the verification are dropped, so they will be read again.
+                if backend.verify_page(idx, page).is_ok() {
                     page.store_state(PageState::UpToDate);
+                } else {
+                    pages.pop(&idx);
                 }
             }
             self.waiter.clear();
@@ -310,6 +317,7 @@ impl ReadaheadState {
         let Some(window) = &self.ra_window else {
             return_errno!(Errno::EINVAL)
         };
+        let _plug = backend.plugged();
         for async_idx in window.readahead_range() {
             let mut async_page = CachePage::alloc_uninit()?;
             let pg_waiter = backend.read_page_async(async_idx, &async_page)?;
@@ -317,6 +325,9 @@ impl ReadaheadState {
                 self.waiter.concat(pg_waiter);
             } else {
                 // Some backends (e.g. RamFS) do not issue requests, but fill the page directly.
+                if backend.verify_page(async_idx, &async_page).is_err() {
+                    continue;
+                }
                 async_page.store_state(PageState::UpToDate);
             }
             pages.put(async_idx, async_page);
@@ -365,11 +376,14 @@ impl PageCacheManager {
         let mut pages = self.pages.lock();
         let backend = self.backend();
         let backend_npages = backend.npages();
-        for idx in page_idx_range.start..page_idx_range.end {
-            if let Some(page) = pages.peek(&idx) {
-                if page.load_state() == PageState::Dirty && idx < backend_npages {
-                    let waiter = backend.write_page_async(idx, page)?;
-                    bio_waiter.concat(waiter);
+        {
+            let _plug = backend.plugged();
+            for idx in page_idx_range.start..page_idx_range.end {
+                if let Some(page) = pages.peek(&idx) {
+                    if page.load_state() == PageState::Dirty && idx < backend_npages {
+                        let waiter = backend.write_page_async(idx, page)?;
+                        bio_waiter.concat(waiter);
+                    }
                 }
             }
         }
@@ -394,7 +408,7 @@ impl PageCacheManager {
         let backend = self.backend();
         // Checks for the previous readahead.
         if ra_state.prev_readahead_is_completed() {
-            ra_state.wait_for_prev_readahead(&mut pages)?;
+            ra_state.wait_for_prev_readahead(&mut pages, backend.as_ref())?;
         }
         // There are three possible conditions that could be encountered upon reaching here.
         // 1. The requested page is ready for read in page cache.
@@ -406,8 +420,10 @@ impl PageCacheManager {
                 // Cond 2: We should wait for the previous readahead.
                 // If there is no previous readahead, an error must have occurred somewhere.
                 assert!(ra_state.request_number() != 0);
-                ra_state.wait_for_prev_readahead(&mut pages)?;
-                pages.get(&idx).unwrap().clone()
+                ra_state.wait_for_prev_readahead(&mut pages, backend.as_ref())?;
+                pages.get(&idx).cloned().ok_or_else(|| {
+                    Error::with_message(Errno::EIO, "the page fails the verification")
+                })?
             } else {
                 // Cond 1.
                 page.clone()
@@ -418,6 +434,7 @@ impl PageCacheManager {
             let page = if idx < backend.npages() {
                 let mut page = CachePage::alloc_uninit()?;
                 backend.read_page(idx, &page)?;
+                backend.verify_page(idx, &page)?;
                 page.store_state(PageState::UpToDate);
                 page
             } else {
@@ -589,11 +606,30 @@ pub trait PageCacheBackend: Sync + Send {
     fn read_page_async(&self, idx: usize, frame: &CachePage) -> Result<BioWaiter>;
     /// Writes a page to the backend asynchronously.
     fn write_page_async(&self, idx: usize, frame: &CachePage) -> Result<BioWaiter>;
+    /// Checks a page read from the backend before it is cached.
+    ///
+    /// The backends of the files with their integrity protected (e.g., by fs-verity) should
+    /// override this method to verify the page. By default, the page is not checked.
+    fn verify_page(&self, _idx: usize, _frame: &CachePage) -> Result<()> {
+        Ok(())
+    }
     /// Returns the number of pages in the backend.
     fn npages(&self) -> usize;
+    /// Starts batching the I/O requests of the following page reads and writes.
+    fn plug(&self) {}
+    /// Ends the batch started by `plug`.
+    fn unplug(&self) {}
 }
 
 impl dyn PageCacheBackend {
+    /// Plugs the backend until the returned guard is dropped.
+    ///
+    /// The guard must be dropped before waiting for the submitted requests.
+    fn plugged(&self) -> BackendPlug<'_> {
+        self.plug();
+        BackendPlug(self)
+    }
+
     /// Reads a page from the backend synchronously.
     fn read_page(&self, idx: usize, frame: &CachePage) -> Result<()> {
         let waiter = self.read_page_async(idx, frame)?;
@@ -611,3 +647,12 @@ impl dyn PageCacheBackend {
         }
     }
 }
+
+/// A guard that unplugs the page cache backend when dropped.
+struct BackendPlug<'a>(&'a dyn PageCacheBackend);
+
+impl Drop for BackendPlug<'_> {
+    fn drop(&mut self) {
+        self.0.unplug();
+    }
+}
@@ -9,11 +9,59 @@ pub use self::{
     builder::RangeLockItemBuilder,
     range::{FileRange, OverlapWith, OFFSET_MAX},
 };
-use crate::{prelude::*, process::Pid};
+use crate::{
+    prelude::*,
+    process::{posix_thread::AsPosixThread, Pid},
+    thread::Tid,
+};
 
 mod builder;
 mod range;
 
+/// The threads that are blocked by the range locks, mapped to the edges in the graph of the
+/// owners waiting for each other.
+///
+/// The graph is keyed by the blocked threads rather than the owners, since multiple threads of
+/// the same owner may be blocked by different owners at the same time. A blocking request fails
+/// with `EDEADLK` if it would close a cycle in this graph.
+static BLOCKED_OWNERS: SpinLock<BTreeMap<Tid, BlockedOwner>> = SpinLock::new(BTreeMap::new());
+
+/// An edge in the graph of the owners waiting for each other.
+#[derive(Debug, Clone, Copy)]
+struct BlockedOwner {
+    /// The owner that is blocked.
+    owner: Pid,
+    /// The owner of the lock that blocks `owner`.
+    blocker: Pid,
+}
+
+/// The maximum length of the chains of the blocked owners to follow, which is the same as Linux.
+const MAX_DEADLOCK_ITERATIONS: usize = 10;
+
+/// Checks whether `owner` would deadlock if it waits for `blocker`.
+fn would_deadlock(blocked_owners: &BTreeMap<Tid, BlockedOwner>, owner: Pid, blocker: Pid) -> bool {
+    let mut blockers = BTreeSet::from([blocker]);
+    for _ in 0..MAX_DEADLOCK_ITERATIONS {
+        if blockers.contains(&owner) {
+            return true;
+        }
+        blockers = blocked_owners
+            .values()
+            .filter(|edge| blockers.contains(&edge.owner))
+            .map(|edge| edge.blocker)
+            .collect();
+        if blockers.is_empty() {
+            return false;
+        }
+    }
+    false
+}
+
+/// Returns the ID of the current thread, which is the key of its edge in [`BLOCKED_OWNERS`].
+fn current_tid() -> Tid {
+    current_thread!().as_posix_thread().unwrap().tid()
+}
+
 /// The metadata of a POSIX advisory file range lock.
 #[derive(Debug, Clone)]
 struct RangeLock {
@@ -125,7 +173,7 @@ impl RangeLockItem {
     /// Sets a new ending position for the lock range
     /// If the range shrinks, it will wake all waiting processes
     pub fn set_end(&mut self, new_end: usize) {
-        let change = self.range().set_end(new_end).expect("invalid new end");
+        let change = self.lock.range.set_end(new_end).expect("invalid new end");
         if let FileRangeChange::Shrunk = change {
             self.wake_all();
         }
@@ -223,12 +271,28 @@ impl RangeLockList {
     ///
     /// If no conflicting locks exist, the lock is set and the function returns `Ok(())`.
     /// If a conflicting lock exists:
-    /// - If waker is not `None`, it is added to the conflicting lock's waitqueue, and the function returns `EAGAIN`.
+    /// - If waker is not `None`, it is added to the conflicting lock's waitqueue, and the function returns `EAGAIN`,
+    ///   unless waiting for the lock would deadlock, in which case the function returns `EDEADLK`.
     /// - If waker is `None`, the function returns `EAGAIN`.
     fn try_set_lock(&self, req_lock: &RangeLockItem, waker: Option<&Arc<Waker>>) -> Result<()> {
         let mut list = self.inner.write();
         if let Some(conflict_lock) = list.iter().find(|l| req_lock.conflict_with(l)) {
             if let Some(waker) = waker {
+                let mut blocked_owners = BLOCKED_OWNERS.lock();
+                if would_deadlock(&blocked_owners, req_lock.owner(), conflict_lock.owner()) {
+                    return_errno_with_message!(
+                        Errno::EDEADLK,
+                        "waiting for the lock would deadlock"
+                    );
+                }
+                blocked_owners.insert(
+                    current_tid(),
+                    BlockedOwner {
+                        owner: req_lock.owner(),
+                        blocker: conflict_lock.owner(),
+                    },
+                );
+                drop(blocked_owners);
                 conflict_lock.waitqueue.enqueue(waker.clone());
             }
             return_errno_with_message!(Errno::EAGAIN, "the file is locked");
@@ -242,6 +306,8 @@ impl RangeLockList {
     ///
     /// If the lock is non-blocking and there is a conflict, return `Err(Errno::EAGAIN)`.
     /// Otherwise, block the current process until the lock can be set or it is interrupted by a signal.
+    /// If the owner of the conflicting lock is waiting for the current process, directly or
+    /// through other processes, return `Err(Errno::EDEADLK)` instead of blocking.
     pub fn set_lock(&self, req_lock: &RangeLockItem, is_nonblocking: bool) -> Result<()> {
         debug!(
             "set_lock with RangeLock: {:?}, is_nonblocking: {}",
@@ -251,14 +317,16 @@ impl RangeLockList {
             self.try_set_lock(req_lock, None)
         } else {
             let (waiter, waker) = Waiter::new_pair();
-            waiter.pause_until(|| {
+            let result = waiter.pause_until(|| {
                 let result = self.try_set_lock(req_lock, Some(&waker));
                 if result.is_err_and(|err| err.error() == Errno::EAGAIN) {
                     None
                 } else {
                     Some(result)
                 }
-            })?
+            });
+            BLOCKED_OWNERS.lock().remove(&current_tid());
+            result?
         }
     }
 
@@ -421,3 +489,44 @@ pub enum RangeLockType {
     WriteLock = 1,
     Unlock = 2,
 }
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    fn edge(owner: Pid, blocker: Pid) -> BlockedOwner {
+        BlockedOwner { owner, blocker }
+    }
+
+    #[ktest]
+    fn deadlock_detection() {
+        let mut blocked_owners = BTreeMap::new();
+        assert!(!would_deadlock(&blocked_owners, 1, 2));
+
+        // 2 waits for 3, and 3 waits for 1.
+        blocked_owners.insert(20, edge(2, 3));
+        blocked_owners.insert(30, edge(3, 1));
+        assert!(would_deadlock(&blocked_owners, 1, 2));
+        assert!(!would_deadlock(&blocked_owners, 4, 2));
+    }
+
+    #[ktest]
+    fn deadlock_detection_multiple_threads() {
+        let mut blocked_owners = BTreeMap::new();
+
+        // Two threads of 2 wait for 3 and 4, respectively, and 4 waits for 1.
+        blocked_owners.insert(20, edge(2, 3));
+        blocked_owners.insert(21, edge(2, 4));
+        blocked_owners.insert(40, edge(4, 1));
+        assert!(would_deadlock(&blocked_owners, 1, 2));
+
+        // The edge of the other thread of 2 is kept after the first thread wakes up.
+        blocked_owners.remove(&20);
+        assert!(would_deadlock(&blocked_owners, 1, 2));
+
+        blocked_owners.remove(&21);
+        assert!(!would_deadlock(&blocked_owners, 1, 2));
+    }
+}
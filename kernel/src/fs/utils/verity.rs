@@ -0,0 +1,471 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! fs-verity, the integrity protection of read-only files.
+//!
+//! The data of a verity file is covered by a Merkle tree, whose root hash is kept in the
+//! fs-verity descriptor of the file. The digest of the descriptor identifies the contents of the
+//! file, and is reported by `FS_IOC_MEASURE_VERITY`. A verity file is read-only, and the file
+//! systems verify the data read from the storage against the Merkle tree before caching it, so
+//! the tampered data cannot be read.
+//!
+//! The Merkle tree and the descriptor are built in the same way as Linux, so the digests agree
+//! with those computed by the `fsverity` utility. Only the block size of [`PAGE_SIZE`] is
+//! supported, and the built-in signatures are not supported.
+//!
+//! Reference: <https://docs.kernel.org/filesystems/fsverity.html>
+
+use align_ext::AlignExt;
+use aster_crypto::api::{self, Hash};
+
+use super::{AccessMode, Inode, InodeType, Permission};
+use crate::prelude::*;
+
+/// The maximum length of the digests.
+const MAX_DIGEST_LEN: usize = 64;
+
+/// The maximum length of the salts.
+const MAX_SALT_LEN: usize = 32;
+
+/// The base 2 logarithm of the block size.
+const LOG_BLOCK_SIZE: u8 = PAGE_SIZE.trailing_zeros() as u8;
+
+const METADATA_TYPE_MERKLE_TREE: u64 = 1;
+const METADATA_TYPE_DESCRIPTOR: u64 = 2;
+const METADATA_TYPE_SIGNATURE: u64 = 3;
+
+/// Serializes the enabling of fs-verity, which should not run concurrently on the same file.
+static ENABLE_LOCK: Mutex<()> = Mutex::new(());
+
+static ZERO_BLOCK: [u8; PAGE_SIZE] = [0; PAGE_SIZE];
+
+/// The hash algorithms of the Merkle trees.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromInt)]
+pub enum HashAlgorithm {
+    Sha256 = 1,
+    Sha512 = 2,
+}
+
+impl HashAlgorithm {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Sha256 => "sha256",
+            Self::Sha512 => "sha512",
+        }
+    }
+}
+
+/// The parameters to enable fs-verity with.
+#[derive(Debug)]
+pub struct FsVerityParams {
+    hash_algorithm: HashAlgorithm,
+    salt: Vec<u8>,
+}
+
+/// The fs-verity descriptor, which is persisted by the file systems.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub struct FsVerityDescriptor {
+    version: u8,
+    hash_algorithm: u8,
+    log_block_size: u8,
+    salt_size: u8,
+    sig_size: u32,
+    data_size: u64,
+    root_hash: [u8; MAX_DIGEST_LEN],
+    salt: [u8; MAX_SALT_LEN],
+    reserved: [u8; 144],
+}
+
+/// The fs-verity state of a file.
+pub struct FsVerity {
+    descriptor: FsVerityDescriptor,
+    hash: Arc<dyn Hash>,
+    /// The salt padded to the block length of the hash function, which is prepended to every
+    /// hashed block.
+    padded_salt: Vec<u8>,
+    /// The levels of the Merkle tree, from the leaf level to the root level.
+    ///
+    /// If the data has only one block, the tree is empty and the root hash is the hash of the
+    /// block.
+    levels: Vec<Vec<u8>>,
+    /// Whether the Merkle tree built from the data does not match the descriptor.
+    is_corrupted: bool,
+}
+
+impl FsVerity {
+    /// Builds the Merkle tree over the data of `data_size` bytes.
+    ///
+    /// `read_block` reads the block of the index to the buffer. The bytes after the end of the
+    /// data are ignored.
+    pub fn build(
+        params: &FsVerityParams,
+        data_size: usize,
+        read_block: &mut dyn FnMut(usize, &mut [u8]) -> Result<()>,
+    ) -> Result<Self> {
+        let mut descriptor = FsVerityDescriptor::new_zeroed();
+        descriptor.version = 1;
+        descriptor.hash_algorithm = params.hash_algorithm as u8;
+        descriptor.log_block_size = LOG_BLOCK_SIZE;
+        descriptor.salt_size = params.salt.len() as u8;
+        descriptor.data_size = (data_size as u64).to_le();
+        descriptor.salt[..params.salt.len()].copy_from_slice(&params.salt);
+
+        let mut verity = Self::new(descriptor)?;
+        let root_hash = verity.build_tree(read_block)?;
+        verity.descriptor.root_hash = root_hash;
+        Ok(verity)
+    }
+
+    /// Loads the fs-verity state from the persisted descriptor.
+    ///
+    /// The Merkle tree is rebuilt over the data of `data_size` bytes, see [`Self::build`]. If the
+    /// tree does not match the descriptor, the state is loaded, but all the blocks fail the
+    /// verification.
+    pub fn load(
+        descriptor: FsVerityDescriptor,
+        data_size: usize,
+        read_block: &mut dyn FnMut(usize, &mut [u8]) -> Result<()>,
+    ) -> Result<Self> {
+        if descriptor.version != 1
+            || descriptor.log_block_size != LOG_BLOCK_SIZE
+            || descriptor.salt_size as usize > MAX_SALT_LEN
+            || descriptor.sig_size != 0
+            || descriptor.reserved.iter().any(|&byte| byte != 0)
+        {
+            return_errno_with_message!(Errno::EINVAL, "the fs-verity descriptor is invalid");
+        }
+
+        let mut verity = Self::new(descriptor)?;
+        if data_size as u64 != u64::from_le(descriptor.data_size) {
+            verity.is_corrupted = true;
+        } else {
+            let root_hash = verity.build_tree(read_block)?;
+            verity.is_corrupted = root_hash != descriptor.root_hash;
+        }
+        if verity.is_corrupted {
+            warn!("the data does not match the fs-verity descriptor");
+        }
+        Ok(verity)
+    }
+
+    fn new(descriptor: FsVerityDescriptor) -> Result<Self> {
+        let hash_algorithm = HashAlgorithm::try_from(descriptor.hash_algorithm)?;
+        let hash = api::find_hash(hash_algorithm.name()).ok_or_else(|| {
+            Error::with_message(Errno::EINVAL, "the hash algorithm is not available")
+        })?;
+
+        let salt = &descriptor.salt[..descriptor.salt_size as usize];
+        let mut padded_salt = salt.to_vec();
+        padded_salt.resize(salt.len().align_up(hash.block_len()), 0);
+
+        Ok(Self {
+            descriptor,
+            hash,
+            padded_salt,
+            levels: Vec::new(),
+            is_corrupted: false,
+        })
+    }
+
+    /// Builds the Merkle tree and returns the root hash.
+    fn build_tree(
+        &mut self,
+        read_block: &mut dyn FnMut(usize, &mut [u8]) -> Result<()>,
+    ) -> Result<[u8; MAX_DIGEST_LEN]> {
+        let digest_len = self.hash.digest_len();
+        let mut root_hash = [0u8; MAX_DIGEST_LEN];
+
+        // The root hash of empty files is all zeros.
+        let nblocks = self.data_size().div_ceil(PAGE_SIZE);
+        if nblocks == 0 {
+            return Ok(root_hash);
+        }
+
+        let mut hashes = vec![0u8; nblocks * digest_len];
+        let mut block = vec![0u8; PAGE_SIZE];
+        for (idx, hash) in hashes.chunks_exact_mut(digest_len).enumerate() {
+            read_block(idx, &mut block)?;
+            self.hash_data_block(idx, &block, hash);
+        }
+
+        // Pack the hashes into blocks and hash the blocks until only one block remains.
+        while hashes.len() > digest_len {
+            let mut level = hashes;
+            level.resize(level.len().align_up(PAGE_SIZE), 0);
+
+            hashes = vec![0u8; level.len() / PAGE_SIZE * digest_len];
+            for (block, hash) in level
+                .chunks_exact(PAGE_SIZE)
+                .zip(hashes.chunks_exact_mut(digest_len))
+            {
+                self.hash_block(block, hash);
+            }
+            self.levels.push(level);
+        }
+
+        root_hash[..digest_len].copy_from_slice(&hashes);
+        Ok(root_hash)
+    }
+
+    /// Hashes the data block of the index, ignoring the bytes after the end of the data.
+    fn hash_data_block(&self, idx: usize, block: &[u8], out: &mut [u8]) {
+        let valid_len = (self.data_size() - idx * PAGE_SIZE).min(PAGE_SIZE);
+
+        let mut state = self.hash.new_state();
+        state.update(&self.padded_salt);
+        state.update(&block[..valid_len]);
+        state.update(&ZERO_BLOCK[valid_len..]);
+        state.finalize(out);
+    }
+
+    fn hash_block(&self, block: &[u8], out: &mut [u8]) {
+        let mut state = self.hash.new_state();
+        state.update(&self.padded_salt);
+        state.update(block);
+        state.finalize(out);
+    }
+
+    /// Verifies the data block of the index against the Merkle tree.
+    pub fn verify_block(&self, idx: usize, block: &[u8]) -> Result<()> {
+        if self.is_corrupted {
+            return_errno_with_message!(Errno::EIO, "the data does not match the descriptor");
+        }
+
+        if idx * PAGE_SIZE >= self.data_size() {
+            return_errno_with_message!(Errno::EIO, "the block is out of the verity file");
+        }
+
+        let digest_len = self.hash.digest_len();
+        let leaf_hashes = match self.levels.first() {
+            Some(level) => level.as_slice(),
+            None => &self.descriptor.root_hash[..digest_len],
+        };
+        let expected = &leaf_hashes[idx * digest_len..(idx + 1) * digest_len];
+
+        let mut hash = [0u8; MAX_DIGEST_LEN];
+        self.hash_data_block(idx, block, &mut hash[..digest_len]);
+        if hash[..digest_len] != *expected {
+            return_errno_with_message!(Errno::EIO, "the block does not match the Merkle tree");
+        }
+        Ok(())
+    }
+
+    /// Returns the descriptor to be persisted.
+    pub fn descriptor(&self) -> &FsVerityDescriptor {
+        &self.descriptor
+    }
+
+    /// Returns whether the data does not match the descriptor.
+    pub fn is_corrupted(&self) -> bool {
+        self.is_corrupted
+    }
+
+    fn data_size(&self) -> usize {
+        u64::from_le(self.descriptor.data_size) as usize
+    }
+
+    /// Returns the digest of the file, which is the hash of the descriptor.
+    fn digest(&self) -> Vec<u8> {
+        let mut digest = vec![0u8; self.hash.digest_len()];
+        self.hash.digest(self.descriptor.as_bytes(), &mut digest);
+        digest
+    }
+
+    /// Iterates over the levels of the Merkle tree from the root level to the leaf level, which
+    /// is the order that Linux stores them.
+    fn merkle_tree(&self) -> impl Iterator<Item = &[u8]> {
+        self.levels.iter().rev().map(Vec::as_slice)
+    }
+}
+
+impl Debug for FsVerity {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("FsVerity")
+            .field("descriptor", &self.descriptor)
+            .field("is_corrupted", &self.is_corrupted)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The argument of `FS_IOC_ENABLE_VERITY`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct EnableArg {
+    version: u32,
+    hash_algorithm: u32,
+    block_size: u32,
+    salt_size: u32,
+    salt_ptr: u64,
+    sig_size: u32,
+    reserved1: u32,
+    sig_ptr: u64,
+    reserved2: [u64; 11],
+}
+
+/// The header of the argument of `FS_IOC_MEASURE_VERITY`, which is followed by the digest.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct DigestHeader {
+    digest_algorithm: u16,
+    digest_size: u16,
+}
+
+/// The argument of `FS_IOC_READ_VERITY_METADATA`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct ReadMetadataArg {
+    metadata_type: u64,
+    offset: u64,
+    length: u64,
+    buf_ptr: u64,
+    reserved: u64,
+}
+
+/// Handles `FS_IOC_ENABLE_VERITY` on a file opened with `access_mode`.
+pub fn ioctl_enable_verity(inode: &dyn Inode, access_mode: AccessMode, arg: Vaddr) -> Result<()> {
+    let user_space = current_userspace!();
+    let arg: EnableArg = user_space.read_val(arg)?;
+
+    if arg.version != 1 {
+        return_errno_with_message!(Errno::EINVAL, "the fs-verity version is not supported");
+    }
+    let hash_algorithm = u8::try_from(arg.hash_algorithm)
+        .ok()
+        .and_then(|algorithm| HashAlgorithm::try_from(algorithm).ok())
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "the hash algorithm is not supported"))?;
+    if arg.block_size as usize != PAGE_SIZE {
+        return_errno_with_message!(Errno::EINVAL, "the block size is not supported");
+    }
+    if arg.salt_size as usize > MAX_SALT_LEN {
+        return_errno_with_message!(Errno::EMSGSIZE, "the salt is too long");
+    }
+    if arg.sig_size != 0 {
+        return_errno_with_message!(Errno::EINVAL, "the built-in signatures are not supported");
+    }
+    if arg.reserved1 != 0 || arg.reserved2.iter().any(|&reserved| reserved != 0) {
+        return_errno_with_message!(Errno::EINVAL, "the reserved fields are not zero");
+    }
+
+    let mut salt = vec![0u8; arg.salt_size as usize];
+    if !salt.is_empty() {
+        user_space.read_bytes(
+            arg.salt_ptr as Vaddr,
+            &mut VmWriter::from(salt.as_mut_slice()),
+        )?;
+    }
+
+    match inode.type_() {
+        InodeType::File => (),
+        InodeType::Dir => return_errno_with_message!(Errno::EISDIR, "the file is a directory"),
+        _ => return_errno_with_message!(Errno::EINVAL, "the file is not a regular file"),
+    }
+    // TODO: Check the other file descriptors and the memory mappings that are writable.
+    if access_mode.is_writable() {
+        return_errno_with_message!(Errno::ETXTBSY, "the file is open for writing");
+    }
+    inode.check_permission(Permission::MAY_WRITE)?;
+
+    let _guard = ENABLE_LOCK.lock();
+    if inode.verity()?.is_some() {
+        return_errno_with_message!(Errno::EEXIST, "fs-verity is already enabled");
+    }
+    inode.enable_verity(&FsVerityParams {
+        hash_algorithm,
+        salt,
+    })
+}
+
+/// Handles `FS_IOC_MEASURE_VERITY`.
+pub fn ioctl_measure_verity(inode: &dyn Inode, arg: Vaddr) -> Result<()> {
+    let verity = get_verity(inode)?;
+
+    let user_space = current_userspace!();
+    let header: DigestHeader = user_space.read_val(arg)?;
+    let digest = verity.digest();
+    if (header.digest_size as usize) < digest.len() {
+        return_errno_with_message!(Errno::EOVERFLOW, "the digest buffer is too small");
+    }
+
+    let header = DigestHeader {
+        digest_algorithm: verity.descriptor.hash_algorithm as u16,
+        digest_size: digest.len() as u16,
+    };
+    user_space.write_val(arg, &header)?;
+    user_space.write_bytes(
+        arg + size_of::<DigestHeader>(),
+        &mut VmReader::from(digest.as_slice()),
+    )?;
+    Ok(())
+}
+
+/// Handles `FS_IOC_READ_VERITY_METADATA`, returning the number of bytes read.
+pub fn ioctl_read_verity_metadata(inode: &dyn Inode, arg: Vaddr) -> Result<usize> {
+    let user_space = current_userspace!();
+    let arg: ReadMetadataArg = user_space.read_val(arg)?;
+    if arg.reserved != 0 {
+        return_errno_with_message!(Errno::EINVAL, "the reserved field is not zero");
+    }
+    let verity = get_verity(inode)?;
+
+    let offset = usize::try_from(arg.offset).unwrap_or(usize::MAX);
+    let length = (arg.length as usize).min(i32::MAX as usize);
+    let mut copier = MetadataCopier {
+        offset,
+        end: offset.saturating_add(length),
+        pos: 0,
+        buf_addr: arg.buf_ptr as Vaddr,
+        user_space: &user_space,
+    };
+    match arg.metadata_type {
+        METADATA_TYPE_MERKLE_TREE => {
+            for level in verity.merkle_tree() {
+                copier.copy(level)?;
+            }
+        }
+        METADATA_TYPE_DESCRIPTOR => copier.copy(verity.descriptor.as_bytes())?,
+        METADATA_TYPE_SIGNATURE => {
+            return_errno_with_message!(Errno::ENODATA, "the file has no built-in signature")
+        }
+        _ => return_errno_with_message!(Errno::EINVAL, "the metadata type is invalid"),
+    }
+
+    Ok(copier.copied_len())
+}
+
+fn get_verity(inode: &dyn Inode) -> Result<Arc<FsVerity>> {
+    inode
+        .verity()?
+        .ok_or_else(|| Error::with_message(Errno::ENODATA, "fs-verity is not enabled"))
+}
+
+/// Copies the range of the concatenated metadata to the user space.
+struct MetadataCopier<'a> {
+    offset: usize,
+    end: usize,
+    /// The position of the next copied piece in the concatenated metadata.
+    pos: usize,
+    buf_addr: Vaddr,
+    user_space: &'a CurrentUserSpace<'a>,
+}
+
+impl MetadataCopier<'_> {
+    fn copy(&mut self, piece: &[u8]) -> Result<()> {
+        let piece_start = self.pos;
+        self.pos += piece.len();
+
+        let start = self.offset.max(piece_start);
+        let end = self.end.min(self.pos);
+        if start < end {
+            self.user_space.write_bytes(
+                self.buf_addr + (start - self.offset),
+                &mut VmReader::from(&piece[start - piece_start..end - piece_start]),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn copied_len(&self) -> usize {
+        self.end.min(self.pos).saturating_sub(self.offset)
+    }
+}
@@ -0,0 +1,188 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The mapping from the bytes of a file to the bytes of the device.
+//!
+//! The regions of a FAT volume are only aligned to sectors, and a cluster may be
+//! smaller than a page. So a page of a cache may be backed by several discontiguous
+//! ranges of the device, and only the bytes inside these ranges may be written back.
+
+use core::ops::Range;
+
+use aster_block::{bio::BioWaiter, BlockDevice};
+use ostd::mm::VmIo;
+
+use super::fat::ClusterId;
+use crate::{
+    fs::utils::{CachePage, PageCacheBackend},
+    prelude::*,
+};
+
+/// The placement of the clusters in the data region.
+#[derive(Clone, Copy, Debug)]
+pub(super) struct ClusterLayout {
+    pub data_offset: usize,
+    pub cluster_size: usize,
+}
+
+impl ClusterLayout {
+    fn cluster_offset(&self, cluster: ClusterId) -> usize {
+        self.data_offset + (cluster - super::fat::FIRST_DATA_CLUSTER) as usize * self.cluster_size
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(super) enum BlockMap {
+    /// A contiguous region of the device, e.g., the FAT or the root directory of FAT12/16.
+    Region { offset: usize, size: usize },
+    /// A cluster chain.
+    Clusters {
+        clusters: Vec<ClusterId>,
+        layout: ClusterLayout,
+    },
+}
+
+impl BlockMap {
+    pub fn new_clusters(clusters: Vec<ClusterId>, layout: ClusterLayout) -> Self {
+        Self::Clusters { clusters, layout }
+    }
+
+    /// Returns the number of bytes that are mapped.
+    pub fn size(&self) -> usize {
+        match self {
+            Self::Region { size, .. } => *size,
+            Self::Clusters { clusters, layout } => clusters.len() * layout.cluster_size,
+        }
+    }
+
+    /// Returns the clusters of the chain, which is empty for a region.
+    pub fn clusters(&self) -> &[ClusterId] {
+        match self {
+            Self::Region { .. } => &[],
+            Self::Clusters { clusters, .. } => clusters,
+        }
+    }
+
+    /// Returns the mutable clusters of the chain.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the map is a region.
+    pub fn clusters_mut(&mut self) -> &mut Vec<ClusterId> {
+        match self {
+            Self::Region { .. } => panic!("a region has no clusters"),
+            Self::Clusters { clusters, .. } => clusters,
+        }
+    }
+
+    pub fn first_cluster(&self) -> Option<ClusterId> {
+        self.clusters().first().copied()
+    }
+
+    /// Returns the device ranges that back the bytes in `range`, which is clipped to the
+    /// mapped size. Each item is a pair of the offset in `range` and the device range.
+    pub fn extents(&self, range: Range<usize>) -> Vec<(usize, Range<usize>)> {
+        let end = range.end.min(self.size());
+        if range.start >= end {
+            return Vec::new();
+        }
+
+        match self {
+            Self::Region { offset, .. } => vec![(0, offset + range.start..offset + end)],
+            Self::Clusters { clusters, layout } => {
+                let mut extents: Vec<(usize, Range<usize>)> = Vec::new();
+                let mut pos = range.start;
+                while pos < end {
+                    let cluster = clusters[pos / layout.cluster_size];
+                    let in_cluster = pos % layout.cluster_size;
+                    let len = (layout.cluster_size - in_cluster).min(end - pos);
+                    let device_start = layout.cluster_offset(cluster) + in_cluster;
+
+                    match extents.last_mut() {
+                        Some((_, last)) if last.end == device_start => last.end += len,
+                        _ => extents.push((pos - range.start, device_start..device_start + len)),
+                    }
+                    pos += len;
+                }
+                extents
+            }
+        }
+    }
+
+    /// Reads the page at `idx` into `frame`, where the unmapped bytes are filled with zeros.
+    pub fn read_page(
+        &self,
+        device: &Arc<dyn BlockDevice>,
+        idx: usize,
+        frame: &CachePage,
+    ) -> Result<BioWaiter> {
+        let page_start = idx * PAGE_SIZE;
+        let mut buf = vec![0u8; PAGE_SIZE];
+        for (offset, device_range) in self.extents(page_start..page_start + PAGE_SIZE) {
+            let len = device_range.len();
+            device.read_bytes(device_range.start, &mut buf[offset..offset + len])?;
+        }
+        frame.write_bytes(0, &buf)?;
+        Ok(BioWaiter::new())
+    }
+
+    /// Writes the mapped bytes of the page at `idx` from `frame`.
+    pub fn write_page(
+        &self,
+        device: &Arc<dyn BlockDevice>,
+        idx: usize,
+        frame: &CachePage,
+    ) -> Result<BioWaiter> {
+        let page_start = idx * PAGE_SIZE;
+        let extents = self.extents(page_start..page_start + PAGE_SIZE);
+        let mut waiter = BioWaiter::new();
+        if extents.is_empty() {
+            return Ok(waiter);
+        }
+
+        let mut buf = vec![0u8; PAGE_SIZE];
+        frame.read_bytes(0, &mut buf)?;
+        for (offset, device_range) in extents {
+            let len = device_range.len();
+            waiter
+                .concat(device.write_bytes_async(device_range.start, &buf[offset..offset + len])?);
+        }
+        Ok(waiter)
+    }
+}
+
+/// The page cache backend of a fixed region of the device.
+pub(super) struct RegionBackend {
+    device: Arc<dyn BlockDevice>,
+    map: BlockMap,
+}
+
+impl RegionBackend {
+    pub fn new(device: Arc<dyn BlockDevice>, offset: usize, size: usize) -> Self {
+        Self {
+            device,
+            map: BlockMap::Region { offset, size },
+        }
+    }
+}
+
+impl PageCacheBackend for RegionBackend {
+    fn read_page_async(&self, idx: usize, frame: &CachePage) -> Result<BioWaiter> {
+        self.map.read_page(&self.device, idx, frame)
+    }
+
+    fn write_page_async(&self, idx: usize, frame: &CachePage) -> Result<BioWaiter> {
+        self.map.write_page(&self.device, idx, frame)
+    }
+
+    fn npages(&self) -> usize {
+        self.map.size().div_ceil(PAGE_SIZE)
+    }
+
+    fn plug(&self) {
+        self.device.plug();
+    }
+
+    fn unplug(&self) {
+        self.device.unplug();
+    }
+}
@@ -0,0 +1,570 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::format;
+
+use aster_rights::Full;
+use ostd::{mm::VmIo, Pod};
+
+use super::fat::ClusterId;
+use crate::{prelude::*, vm::vmo::Vmo};
+
+pub(super) const DENTRY_SIZE: usize = 32;
+
+/// The maximum length of a long name, in UTF-16 code units.
+pub(super) const MAX_NAME_LEN: usize = 255;
+
+/// The first name byte of a deleted entry.
+pub(super) const DENTRY_DELETED: u8 = 0xE5;
+/// The first name byte of an entry that ends the directory.
+const DENTRY_END: u8 = 0x00;
+/// The first name byte that stands for a real 0xE5, which is a lead byte in Shift JIS.
+const DENTRY_ESCAPED_E5: u8 = 0x05;
+
+const LFN_CHARS_PER_ENTRY: usize = 13;
+/// The flag of the order of the last (and physically the first) long name entry.
+const LFN_LAST: u8 = 0x40;
+const LFN_ORDER_MASK: u8 = 0x1F;
+const MAX_LFN_ENTRIES: usize = MAX_NAME_LEN.div_ceil(LFN_CHARS_PER_ENTRY);
+
+/// The flags of `nt_res`, used by Windows NT to keep the case of short names.
+const CASE_LOWER_BASE: u8 = 0x08;
+const CASE_LOWER_EXT: u8 = 0x10;
+
+bitflags! {
+    pub(super) struct FatAttr: u8 {
+        const READ_ONLY = 0x01;
+        const HIDDEN = 0x02;
+        const SYSTEM = 0x04;
+        const VOLUME_ID = 0x08;
+        const DIRECTORY = 0x10;
+        const ARCHIVE = 0x20;
+    }
+}
+
+/// The attribute value that marks a long name entry.
+const ATTR_LONG_NAME: u8 = 0x0F;
+
+/// A short (8.3) directory entry.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Default, Pod)]
+#[expect(dead_code)]
+pub(super) struct RawDentry {
+    pub name: [u8; 11],
+    pub attr: u8,
+    pub nt_res: u8,
+    pub create_time_tenth: u8,
+    pub create_time: u16,
+    pub create_date: u16,
+    pub access_date: u16,
+    pub cluster_hi: u16,
+    pub write_time: u16,
+    pub write_date: u16,
+    pub cluster_lo: u16,
+    pub size: u32,
+}
+
+impl RawDentry {
+    pub fn attr(&self) -> FatAttr {
+        FatAttr::from_bits_truncate(self.attr)
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.attr().contains(FatAttr::DIRECTORY)
+    }
+
+    pub fn first_cluster(&self) -> ClusterId {
+        ((self.cluster_hi as u32) << 16) | self.cluster_lo as u32
+    }
+
+    pub fn set_first_cluster(&mut self, cluster: ClusterId) {
+        self.cluster_hi = (cluster >> 16) as u16;
+        self.cluster_lo = cluster as u16;
+    }
+
+    pub fn is_dot_or_dotdot(&self) -> bool {
+        self.name == *b".          " || self.name == *b"..         "
+    }
+
+    /// Returns the displayed form of the short name.
+    pub fn short_name(&self) -> String {
+        let mut raw = self.name;
+        if raw[0] == DENTRY_ESCAPED_E5 {
+            raw[0] = DENTRY_DELETED;
+        }
+
+        let decode = |bytes: &[u8], lower: bool| -> String {
+            let end = bytes
+                .iter()
+                .rposition(|byte| *byte != b' ')
+                .map_or(0, |pos| pos + 1);
+            bytes[..end]
+                .iter()
+                .map(|byte| {
+                    let c = char::from(*byte);
+                    if lower {
+                        c.to_ascii_lowercase()
+                    } else {
+                        c
+                    }
+                })
+                .collect()
+        };
+
+        let mut name = decode(&raw[..8], self.nt_res & CASE_LOWER_BASE != 0);
+        let ext = decode(&raw[8..], self.nt_res & CASE_LOWER_EXT != 0);
+        if !ext.is_empty() {
+            name.push('.');
+            name.push_str(&ext);
+        }
+        name
+    }
+}
+
+/// A long name entry, which holds 13 UTF-16 code units of the name.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Default, Pod)]
+#[expect(dead_code)]
+pub(super) struct RawLfnDentry {
+    order: u8,
+    name1: [u8; 10],
+    attr: u8,
+    type_: u8,
+    checksum: u8,
+    name2: [u8; 12],
+    first_cluster: u16,
+    name3: [u8; 4],
+}
+
+impl RawLfnDentry {
+    fn units(&self) -> [u16; LFN_CHARS_PER_ENTRY] {
+        let mut units = [0u16; LFN_CHARS_PER_ENTRY];
+        let bytes = self
+            .name1
+            .iter()
+            .chain(self.name2.iter())
+            .chain(self.name3.iter());
+        let bytes: Vec<u8> = bytes.copied().collect();
+        for (unit, pair) in units.iter_mut().zip(bytes.chunks_exact(2)) {
+            *unit = u16::from_le_bytes([pair[0], pair[1]]);
+        }
+        units
+    }
+
+    fn new(order: u8, checksum: u8, units: &[u16]) -> Self {
+        let mut bytes = [0u8; LFN_CHARS_PER_ENTRY * 2];
+        for (pair, unit) in bytes.chunks_exact_mut(2).zip(units) {
+            pair.copy_from_slice(&unit.to_le_bytes());
+        }
+
+        let mut dentry = Self {
+            order,
+            attr: ATTR_LONG_NAME,
+            checksum,
+            ..Default::default()
+        };
+        dentry.name1.copy_from_slice(&bytes[..10]);
+        dentry.name2.copy_from_slice(&bytes[10..22]);
+        dentry.name3.copy_from_slice(&bytes[22..]);
+        dentry
+    }
+}
+
+/// Computes the checksum of a short name, which is stored in its long name entries.
+pub(super) fn short_name_checksum(name: &[u8; 11]) -> u8 {
+    name.iter().fold(0u8, |sum, byte| {
+        ((sum & 1) << 7).wrapping_add(sum >> 1).wrapping_add(*byte)
+    })
+}
+
+/// Returns whether two names are equal, which are compared case-insensitively like Windows.
+pub(super) fn names_eq(a: &str, b: &str) -> bool {
+    a.chars()
+        .flat_map(char::to_uppercase)
+        .eq(b.chars().flat_map(char::to_uppercase))
+}
+
+/// The directory entries that are going to be written for a new name.
+pub(super) struct NameEntries {
+    pub short_name: [u8; 11],
+    pub nt_res: u8,
+    /// The long name entries in their on-disk order.
+    pub lfn: Vec<RawLfnDentry>,
+}
+
+impl NameEntries {
+    /// The number of directory entries that the name occupies.
+    pub fn len(&self) -> usize {
+        self.lfn.len() + 1
+    }
+
+    /// Returns the bytes of all the entries, where `dentry` is the short entry.
+    pub fn to_bytes(&self, dentry: &RawDentry) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.len() * DENTRY_SIZE);
+        for lfn in self.lfn.iter() {
+            bytes.extend_from_slice(lfn.as_bytes());
+        }
+        let mut dentry = *dentry;
+        dentry.name = self.short_name;
+        dentry.nt_res = self.nt_res;
+        bytes.extend_from_slice(dentry.as_bytes());
+        bytes
+    }
+}
+
+fn is_valid_short_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "!#$%&'()-@^_`{}~".contains(c)
+}
+
+/// Tries to encode `part` directly as a part of a short name, returning the case flag.
+fn encode_short_part(part: &str, buf: &mut [u8], lower_flag: u8) -> Option<u8> {
+    if part.len() > buf.len() || !part.chars().all(is_valid_short_char) {
+        return None;
+    }
+    let has_lower = part.chars().any(|c| c.is_ascii_lowercase());
+    let has_upper = part.chars().any(|c| c.is_ascii_uppercase());
+    if has_lower && has_upper {
+        return None;
+    }
+
+    buf.fill(b' ');
+    for (byte, c) in buf.iter_mut().zip(part.chars()) {
+        *byte = c.to_ascii_uppercase() as u8;
+    }
+    Some(if has_lower { lower_flag } else { 0 })
+}
+
+/// Returns the short name if `name` can be stored without long name entries.
+fn encode_direct_short_name(name: &str) -> Option<([u8; 11], u8)> {
+    let (base, ext) = match name.split_once('.') {
+        Some((base, ext)) => (base, ext),
+        None => (name, ""),
+    };
+    if base.is_empty() || ext.contains('.') || name.ends_with('.') {
+        return None;
+    }
+
+    let mut short_name = [b' '; 11];
+    let base_flag = encode_short_part(base, &mut short_name[..8], CASE_LOWER_BASE)?;
+    let ext_flag = encode_short_part(ext, &mut short_name[8..], CASE_LOWER_EXT)?;
+    if short_name[0] == DENTRY_DELETED {
+        short_name[0] = DENTRY_ESCAPED_E5;
+    }
+    Some((short_name, base_flag | ext_flag))
+}
+
+/// Generates a unique "BASIS~N.EXT" short name for a long name.
+fn generate_short_name(name: &str, exists: &dyn Fn(&[u8; 11]) -> bool) -> Result<[u8; 11]> {
+    let to_short_chars = |part: &str, max_len: usize| -> Vec<u8> {
+        part.chars()
+            .filter(|c| *c != ' ' && *c != '.')
+            .map(|c| {
+                let c = c.to_ascii_uppercase();
+                if is_valid_short_char(c) {
+                    c as u8
+                } else {
+                    b'_'
+                }
+            })
+            .take(max_len)
+            .collect()
+    };
+
+    let trimmed = name.trim_start_matches(['.', ' ']);
+    let (base, ext) = match trimmed.rsplit_once('.') {
+        Some((base, ext)) => (base, ext),
+        None => (trimmed, ""),
+    };
+    let mut basis = to_short_chars(base, 8);
+    if basis.is_empty() {
+        basis.push(b'_');
+    }
+    let ext = to_short_chars(ext, 3);
+
+    for seq in 1..1_000_000u32 {
+        let tail = format!("~{}", seq);
+        let base_len = basis.len().min(8 - tail.len());
+
+        let mut short_name = [b' '; 11];
+        short_name[..base_len].copy_from_slice(&basis[..base_len]);
+        short_name[base_len..base_len + tail.len()].copy_from_slice(tail.as_bytes());
+        short_name[8..8 + ext.len()].copy_from_slice(&ext);
+        if !exists(&short_name) {
+            return Ok(short_name);
+        }
+    }
+    return_errno_with_message!(Errno::EEXIST, "no unique short name is available")
+}
+
+/// Encodes `name` into directory entries, where `exists` tells whether a short name is taken.
+pub(super) fn encode_name(name: &str, exists: &dyn Fn(&[u8; 11]) -> bool) -> Result<NameEntries> {
+    if name.is_empty() || name == "." || name == ".." {
+        return_errno_with_message!(Errno::EINVAL, "invalid file name");
+    }
+    if name
+        .chars()
+        .any(|c| (c as u32) < 0x20 || "\"*/:<>?\\|".contains(c))
+    {
+        return_errno_with_message!(Errno::EINVAL, "invalid character in the file name");
+    }
+    // Windows strips trailing dots and spaces, which would make such names unreachable.
+    if name.ends_with(['.', ' ']) {
+        return_errno_with_message!(Errno::EINVAL, "invalid trailing character in the file name");
+    }
+
+    if let Some((short_name, nt_res)) = encode_direct_short_name(name) {
+        if !exists(&short_name) {
+            return Ok(NameEntries {
+                short_name,
+                nt_res,
+                lfn: Vec::new(),
+            });
+        }
+    }
+
+    let units: Vec<u16> = name.encode_utf16().collect();
+    if units.len() > MAX_NAME_LEN {
+        return_errno_with_message!(Errno::ENAMETOOLONG, "the file name is too long");
+    }
+    let short_name = generate_short_name(name, exists)?;
+    let checksum = short_name_checksum(&short_name);
+
+    let num_entries = units.len().div_ceil(LFN_CHARS_PER_ENTRY);
+    let mut lfn = Vec::with_capacity(num_entries);
+    for index in (0..num_entries).rev() {
+        let start = index * LFN_CHARS_PER_ENTRY;
+        let mut chunk = [0xFFFFu16; LFN_CHARS_PER_ENTRY];
+        let part = &units[start..units.len().min(start + LFN_CHARS_PER_ENTRY)];
+        chunk[..part.len()].copy_from_slice(part);
+        if part.len() < LFN_CHARS_PER_ENTRY {
+            chunk[part.len()] = 0;
+        }
+
+        let mut order = index as u8 + 1;
+        if index == num_entries - 1 {
+            order |= LFN_LAST;
+        }
+        lfn.push(RawLfnDentry::new(order, checksum, &chunk));
+    }
+
+    Ok(NameEntries {
+        short_name,
+        nt_res: 0,
+        lfn,
+    })
+}
+
+/// An entry of a directory, with its long name entries (if any) resolved.
+#[derive(Clone, Debug)]
+pub(super) struct DirEntry {
+    pub name: String,
+    pub dentry: RawDentry,
+    /// The offset of the first entry of the set, which is a long name entry if there is any.
+    pub start: usize,
+    /// The offset of the short entry.
+    pub offset: usize,
+}
+
+struct LfnState {
+    start: usize,
+    checksum: u8,
+    next_order: u8,
+    units: Vec<u16>,
+}
+
+/// An iterator over the entries of a directory, skipping the volume label.
+pub(super) struct DirEntryIter<'a> {
+    pages: &'a Vmo<Full>,
+    offset: usize,
+    end: usize,
+}
+
+impl<'a> DirEntryIter<'a> {
+    pub fn new(pages: &'a Vmo<Full>, offset: usize, end: usize) -> Self {
+        Self { pages, offset, end }
+    }
+
+    /// Returns the offset of the next entry to visit.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    fn next_entry(&mut self) -> Result<Option<DirEntry>> {
+        let mut lfn: Option<LfnState> = None;
+
+        while self.offset + DENTRY_SIZE <= self.end {
+            let offset = self.offset;
+            let mut bytes = [0u8; DENTRY_SIZE];
+            self.pages.read_bytes(offset, &mut bytes)?;
+            self.offset += DENTRY_SIZE;
+
+            match bytes[0] {
+                DENTRY_END => {
+                    self.offset = self.end;
+                    return Ok(None);
+                }
+                DENTRY_DELETED => {
+                    lfn = None;
+                    continue;
+                }
+                _ => {}
+            }
+
+            if bytes[11] & 0x3F == ATTR_LONG_NAME {
+                let entry = RawLfnDentry::from_bytes(&bytes);
+                if entry.order & LFN_LAST != 0 {
+                    let count = (entry.order & LFN_ORDER_MASK) as usize;
+                    lfn = (1..=MAX_LFN_ENTRIES).contains(&count).then(|| LfnState {
+                        start: offset,
+                        checksum: entry.checksum,
+                        next_order: count as u8,
+                        units: vec![0; count * LFN_CHARS_PER_ENTRY],
+                    });
+                }
+                lfn = lfn.filter(|state| {
+                    state.next_order == entry.order & LFN_ORDER_MASK
+                        && state.checksum == entry.checksum
+                });
+                if let Some(state) = lfn.as_mut() {
+                    let start = (state.next_order as usize - 1) * LFN_CHARS_PER_ENTRY;
+                    state.units[start..start + LFN_CHARS_PER_ENTRY].copy_from_slice(&entry.units());
+                    state.next_order -= 1;
+                }
+                continue;
+            }
+
+            let dentry = RawDentry::from_bytes(&bytes);
+            if dentry.attr().contains(FatAttr::VOLUME_ID) {
+                lfn = None;
+                continue;
+            }
+
+            let lfn = lfn.take().filter(|state| {
+                state.next_order == 0 && state.checksum == short_name_checksum(&dentry.name)
+            });
+            let (name, start) = match lfn {
+                Some(state) => {
+                    let len = state
+                        .units
+                        .iter()
+                        .position(|unit| *unit == 0)
+                        .unwrap_or(state.units.len());
+                    let name = char::decode_utf16(state.units[..len].iter().copied())
+                        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                        .collect();
+                    (name, state.start)
+                }
+                None => (dentry.short_name(), offset),
+            };
+
+            return Ok(Some(DirEntry {
+                name,
+                dentry,
+                start,
+                offset,
+            }));
+        }
+
+        Ok(None)
+    }
+}
+
+impl Iterator for DirEntryIter<'_> {
+    type Item = Result<DirEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_entry().transpose()
+    }
+}
+
+/// Finds `count` consecutive free entries in the directory.
+pub(super) fn find_free_slots(
+    pages: &Vmo<Full>,
+    size: usize,
+    count: usize,
+) -> Result<Option<usize>> {
+    let mut run_start = 0;
+    let mut run_len = 0;
+    let mut offset = 0;
+    while offset + DENTRY_SIZE <= size {
+        let first: u8 = pages.read_val(offset)?;
+        if first == DENTRY_END {
+            // All the following entries are free.
+            let start = if run_len > 0 { run_start } else { offset };
+            return Ok(((size - start) / DENTRY_SIZE >= count).then_some(start));
+        }
+
+        if first == DENTRY_DELETED {
+            if run_len == 0 {
+                run_start = offset;
+            }
+            run_len += 1;
+            if run_len == count {
+                return Ok(Some(run_start));
+            }
+        } else {
+            run_len = 0;
+        }
+        offset += DENTRY_SIZE;
+    }
+    Ok(None)
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn checksum() {
+        assert_eq!(short_name_checksum(b"README  TXT"), 0x73);
+    }
+
+    #[ktest]
+    fn direct_short_names() {
+        let none = |_: &[u8; 11]| false;
+
+        let entries = encode_name("README.TXT", &none).unwrap();
+        assert_eq!(&entries.short_name, b"README  TXT");
+        assert_eq!(entries.nt_res, 0);
+        assert!(entries.lfn.is_empty());
+
+        let entries = encode_name("boot.cfg", &none).unwrap();
+        assert_eq!(&entries.short_name, b"BOOT    CFG");
+        assert_eq!(entries.nt_res, CASE_LOWER_BASE | CASE_LOWER_EXT);
+        assert!(entries.lfn.is_empty());
+    }
+
+    #[ktest]
+    fn long_names() {
+        let taken = |name: &[u8; 11]| name == b"VMLINU~1BIN";
+
+        let entries = encode_name("vmlinux-6.6.bin", &taken).unwrap();
+        assert_eq!(&entries.short_name, b"VMLINU~2BIN");
+        assert_eq!(entries.lfn.len(), 2);
+        assert_eq!(entries.lfn[0].order, 2 | LFN_LAST);
+        assert_eq!(entries.lfn[1].order, 1);
+
+        let mut units = entries.lfn[1].units().to_vec();
+        units.extend_from_slice(&entries.lfn[0].units());
+        let len = units.iter().position(|unit| *unit == 0).unwrap();
+        assert_eq!(
+            String::from_utf16(&units[..len]).unwrap(),
+            "vmlinux-6.6.bin"
+        );
+    }
+
+    #[ktest]
+    fn invalid_names() {
+        let none = |_: &[u8; 11]| false;
+        assert!(encode_name("a:b", &none).is_err());
+        assert!(encode_name("trailing.", &none).is_err());
+        assert!(encode_name("..", &none).is_err());
+    }
+
+    #[ktest]
+    fn case_insensitive_names() {
+        assert!(names_eq("EFI", "efi"));
+        assert!(!names_eq("EFI", "efi2"));
+    }
+}
@@ -0,0 +1,334 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use aster_block::BlockDevice;
+use ostd::mm::VmIo;
+
+use super::{block_map::RegionBackend, super_block::VfatSuperBlock};
+use crate::{
+    fs::utils::{PageCache, PageCacheBackend},
+    prelude::*,
+};
+
+pub(super) type ClusterId = u32;
+
+/// Clusters 0 and 1 are reserved, so the data region starts at cluster 2.
+pub(super) const FIRST_DATA_CLUSTER: ClusterId = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum FatType {
+    Fat12,
+    Fat16,
+    Fat32,
+}
+
+impl FatType {
+    /// Returns the mask of the valid bits of an entry.
+    fn mask(&self) -> u32 {
+        match self {
+            Self::Fat12 => 0xFFF,
+            Self::Fat16 => 0xFFFF,
+            Self::Fat32 => 0x0FFF_FFFF,
+        }
+    }
+
+    /// Returns the number of bytes occupied by `num_entries` entries.
+    pub fn fat_bytes(&self, num_entries: usize) -> usize {
+        match self {
+            Self::Fat12 => (num_entries * 3).div_ceil(2),
+            Self::Fat16 => num_entries * 2,
+            Self::Fat32 => num_entries * 4,
+        }
+    }
+
+    fn decode(&self, raw: u32) -> FatEntry {
+        let value = raw & self.mask();
+        match value {
+            0 => FatEntry::Free,
+            value if value == self.mask() - 8 => FatEntry::Bad,
+            value if value >= self.mask() - 7 => FatEntry::EndOfChain,
+            value => FatEntry::Next(value),
+        }
+    }
+
+    fn encode(&self, entry: FatEntry) -> u32 {
+        match entry {
+            FatEntry::Free => 0,
+            FatEntry::Next(cluster) => cluster,
+            FatEntry::Bad => self.mask() - 8,
+            FatEntry::EndOfChain => self.mask(),
+        }
+    }
+}
+
+/// The value of an entry of the FAT.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum FatEntry {
+    Free,
+    Next(ClusterId),
+    Bad,
+    EndOfChain,
+}
+
+/// The file allocation table, with all its copies.
+pub(super) struct FatTable {
+    fat_type: FatType,
+    /// The FAT copies, relative to the first one.
+    copy_offsets: Vec<usize>,
+    read_offset: usize,
+    region_size: usize,
+    num_clusters: u32,
+    cache: PageCache,
+    _backend: Arc<dyn PageCacheBackend>,
+    state: Mutex<FatState>,
+}
+
+#[derive(Debug)]
+struct FatState {
+    free_count: u32,
+    /// The cluster to start searching for free clusters from.
+    next_free: ClusterId,
+}
+
+impl FatTable {
+    pub fn load(block_device: Arc<dyn BlockDevice>, sb: &VfatSuperBlock) -> Result<Self> {
+        let region_size = sb.num_fats * sb.fat_size;
+        let backend: Arc<dyn PageCacheBackend> =
+            Arc::new(RegionBackend::new(block_device, sb.fat_offset, region_size));
+        let cache = PageCache::with_capacity(region_size, Arc::downgrade(&backend))?;
+
+        let table = Self {
+            fat_type: sb.fat_type,
+            copy_offsets: sb
+                .fat_copies()
+                .map(|index| sb.fat_copy_offset(index) - sb.fat_offset)
+                .collect(),
+            read_offset: sb.fat_copy_offset(sb.read_fat()) - sb.fat_offset,
+            region_size,
+            num_clusters: sb.num_clusters,
+            cache,
+            _backend: backend,
+            state: Mutex::new(FatState {
+                free_count: 0,
+                next_free: FIRST_DATA_CLUSTER,
+            }),
+        };
+
+        *table.state.lock() = table.scan_free_clusters()?;
+        Ok(table)
+    }
+
+    fn scan_free_clusters(&self) -> Result<FatState> {
+        const CHUNK_ENTRIES: usize = 1024;
+
+        let mut free_count = 0;
+        let mut first_free = None;
+        let end = FIRST_DATA_CLUSTER + self.num_clusters;
+        let mut cluster = FIRST_DATA_CLUSTER;
+        while cluster < end {
+            let count = (CHUNK_ENTRIES as u32).min(end - cluster);
+            match self.fat_type {
+                // FAT12 volumes are tiny, so read the entries one by one.
+                FatType::Fat12 => {
+                    for cluster in cluster..cluster + count {
+                        if self.read_raw(self.read_offset, cluster)? == 0 {
+                            free_count += 1;
+                            first_free.get_or_insert(cluster);
+                        }
+                    }
+                }
+                FatType::Fat16 | FatType::Fat32 => {
+                    let entry_size = self.fat_type.fat_bytes(1);
+                    let mut buf = vec![0u8; count as usize * entry_size];
+                    self.cache
+                        .pages()
+                        .read_bytes(self.read_offset + cluster as usize * entry_size, &mut buf)?;
+                    for (index, raw) in buf.chunks_exact(entry_size).enumerate() {
+                        let raw = raw
+                            .iter()
+                            .rev()
+                            .fold(0u32, |acc, byte| acc << 8 | *byte as u32);
+                        if self.fat_type.decode(raw) == FatEntry::Free {
+                            free_count += 1;
+                            first_free.get_or_insert(cluster + index as u32);
+                        }
+                    }
+                }
+            }
+            cluster += count;
+        }
+
+        Ok(FatState {
+            free_count,
+            next_free: first_free.unwrap_or(FIRST_DATA_CLUSTER),
+        })
+    }
+
+    pub fn free_count(&self) -> u32 {
+        self.state.lock().free_count
+    }
+
+    pub fn next_free(&self) -> ClusterId {
+        self.state.lock().next_free
+    }
+
+    fn is_valid_cluster(&self, cluster: ClusterId) -> bool {
+        (FIRST_DATA_CLUSTER..FIRST_DATA_CLUSTER + self.num_clusters).contains(&cluster)
+    }
+
+    fn read_raw(&self, copy_offset: usize, cluster: ClusterId) -> Result<u32> {
+        let pages = self.cache.pages();
+        let index = cluster as usize;
+        let raw = match self.fat_type {
+            FatType::Fat12 => {
+                let value: u16 = pages.read_val(copy_offset + index * 3 / 2)?;
+                if index % 2 == 0 {
+                    (value & 0xFFF) as u32
+                } else {
+                    (value >> 4) as u32
+                }
+            }
+            FatType::Fat16 => pages.read_val::<u16>(copy_offset + index * 2)? as u32,
+            FatType::Fat32 => pages.read_val::<u32>(copy_offset + index * 4)?,
+        };
+        Ok(raw)
+    }
+
+    fn write_raw(&self, copy_offset: usize, cluster: ClusterId, value: u32) -> Result<()> {
+        let pages = self.cache.pages();
+        let index = cluster as usize;
+        match self.fat_type {
+            FatType::Fat12 => {
+                let offset = copy_offset + index * 3 / 2;
+                let old: u16 = pages.read_val(offset)?;
+                let new = if index % 2 == 0 {
+                    (old & 0xF000) | (value as u16 & 0xFFF)
+                } else {
+                    (old & 0x000F) | ((value as u16) << 4)
+                };
+                pages.write_val(offset, &new)?;
+            }
+            FatType::Fat16 => pages.write_val(copy_offset + index * 2, &(value as u16))?,
+            FatType::Fat32 => {
+                // The upper 4 bits are reserved and must be preserved.
+                let offset = copy_offset + index * 4;
+                let old: u32 = pages.read_val(offset)?;
+                pages.write_val(offset, &((old & 0xF000_0000) | (value & 0x0FFF_FFFF)))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn read(&self, cluster: ClusterId) -> Result<FatEntry> {
+        if !self.is_valid_cluster(cluster) {
+            return_errno_with_message!(Errno::EINVAL, "invalid cluster id");
+        }
+        let entry = self
+            .fat_type
+            .decode(self.read_raw(self.read_offset, cluster)?);
+        if let FatEntry::Next(next) = entry {
+            if !self.is_valid_cluster(next) {
+                return_errno_with_message!(Errno::EIO, "corrupted cluster chain");
+            }
+        }
+        Ok(entry)
+    }
+
+    fn write(&self, cluster: ClusterId, entry: FatEntry) -> Result<()> {
+        let value = self.fat_type.encode(entry);
+        for copy_offset in self.copy_offsets.iter() {
+            self.write_raw(*copy_offset, cluster, value)?;
+        }
+        Ok(())
+    }
+
+    /// Collects the clusters of the chain starting at `start`.
+    pub fn chain(&self, start: ClusterId) -> Result<Vec<ClusterId>> {
+        let mut clusters = Vec::new();
+        let mut cluster = start;
+        loop {
+            if !self.is_valid_cluster(cluster) || clusters.len() >= self.num_clusters as usize {
+                return_errno_with_message!(Errno::EIO, "corrupted cluster chain");
+            }
+            clusters.push(cluster);
+            match self.read(cluster)? {
+                FatEntry::Next(next) => cluster = next,
+                FatEntry::EndOfChain => return Ok(clusters),
+                FatEntry::Free | FatEntry::Bad => {
+                    return_errno_with_message!(Errno::EIO, "corrupted cluster chain")
+                }
+            }
+        }
+    }
+
+    /// Allocates `count` clusters and links them after `prev`, if any.
+    ///
+    /// The allocation is all or nothing.
+    pub fn alloc(&self, count: usize, prev: Option<ClusterId>) -> Result<Vec<ClusterId>> {
+        let mut state = self.state.lock();
+        if (state.free_count as usize) < count {
+            return_errno_with_message!(Errno::ENOSPC, "no free clusters");
+        }
+
+        let mut clusters = Vec::with_capacity(count);
+        let mut cluster = state.next_free;
+        for _ in 0..self.num_clusters {
+            if clusters.len() == count {
+                break;
+            }
+            if !self.is_valid_cluster(cluster) {
+                cluster = FIRST_DATA_CLUSTER;
+            }
+            if self.read(cluster)? == FatEntry::Free {
+                clusters.push(cluster);
+            }
+            cluster += 1;
+        }
+        if clusters.len() < count {
+            return_errno_with_message!(Errno::ENOSPC, "no free clusters");
+        }
+
+        for pair in clusters.windows(2) {
+            self.write(pair[0], FatEntry::Next(pair[1]))?;
+        }
+        if let Some(last) = clusters.last() {
+            self.write(*last, FatEntry::EndOfChain)?;
+        }
+        if let (Some(prev), Some(first)) = (prev, clusters.first()) {
+            self.write(prev, FatEntry::Next(*first))?;
+        }
+
+        state.free_count -= count as u32;
+        state.next_free = cluster;
+        Ok(clusters)
+    }
+
+    /// Frees `clusters`, which should be the tail of a chain.
+    ///
+    /// If `new_last` is given, it becomes the end of the chain.
+    pub fn free(&self, clusters: &[ClusterId], new_last: Option<ClusterId>) -> Result<()> {
+        let mut state = self.state.lock();
+        if let Some(last) = new_last {
+            self.write(last, FatEntry::EndOfChain)?;
+        }
+        for cluster in clusters {
+            self.write(*cluster, FatEntry::Free)?;
+            state.free_count += 1;
+            state.next_free = state.next_free.min(*cluster);
+        }
+        Ok(())
+    }
+
+    pub fn sync(&self) -> Result<()> {
+        self.cache.evict_range(0..self.region_size)
+    }
+}
+
+impl Debug for FatTable {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("FatTable")
+            .field("fat_type", &self.fat_type)
+            .field("num_clusters", &self.num_clusters)
+            .field("state", &self.state)
+            .finish_non_exhaustive()
+    }
+}
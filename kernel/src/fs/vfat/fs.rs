@@ -0,0 +1,191 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use aster_block::BlockDevice;
+use ostd::mm::VmIo;
+
+use super::{
+    dentry::MAX_NAME_LEN,
+    fat::{FatTable, FatType},
+    inode::VfatInode,
+    super_block::{RawBootSector, RawFsInfo, RootDir, VfatSuperBlock, FS_INFO_UNKNOWN},
+};
+use crate::{
+    fs::utils::{FileSystem, FsFlags, Inode, SuperBlock},
+    prelude::*,
+};
+
+/// The magic number of FAT file systems reported by `statfs`, i.e., `MSDOS_SUPER_MAGIC`.
+const VFAT_MAGIC: u64 = 0x4d44;
+
+pub(super) const VFAT_ROOT_INO: u64 = 1;
+
+#[derive(Debug)]
+pub struct VfatFs {
+    block_device: Arc<dyn BlockDevice>,
+    super_block: VfatSuperBlock,
+    fat: FatTable,
+    mount_options: VfatMountOptions,
+    root: Arc<VfatInode>,
+    /// Used for inode allocation.
+    next_ino: AtomicU64,
+    /// The opened inodes, indexed by the device offsets of their short entries.
+    inodes: RwMutex<BTreeMap<usize, Arc<VfatInode>>>,
+    /// A global lock, which must be held before modifying any directory.
+    mutex: Mutex<()>,
+}
+
+impl VfatFs {
+    pub fn open(
+        block_device: Arc<dyn BlockDevice>,
+        mount_options: VfatMountOptions,
+    ) -> Result<Arc<Self>> {
+        let boot_sector: RawBootSector = block_device.read_val(0)?;
+        let super_block = VfatSuperBlock::try_from(boot_sector)?;
+        if let RootDir::Cluster(cluster) = super_block.root_dir {
+            if !super_block.is_valid_cluster(cluster) {
+                return_errno_with_message!(Errno::EINVAL, "invalid root cluster");
+            }
+        }
+        let fat = FatTable::load(block_device.clone(), &super_block)?;
+
+        let fs = Arc::new_cyclic(|weak_fs| Self {
+            block_device,
+            super_block,
+            fat,
+            mount_options,
+            root: VfatInode::new_root(weak_fs.clone(), &super_block),
+            next_ino: AtomicU64::new(VFAT_ROOT_INO + 1),
+            inodes: RwMutex::new(BTreeMap::new()),
+            mutex: Mutex::new(()),
+        });
+        fs.root.load_root_chain()?;
+
+        Ok(fs)
+    }
+
+    pub(super) fn block_device(&self) -> &Arc<dyn BlockDevice> {
+        &self.block_device
+    }
+
+    pub(super) fn super_block(&self) -> &VfatSuperBlock {
+        &self.super_block
+    }
+
+    pub(super) fn fat(&self) -> &FatTable {
+        &self.fat
+    }
+
+    pub(super) fn mount_options(&self) -> &VfatMountOptions {
+        &self.mount_options
+    }
+
+    pub(super) fn cluster_size(&self) -> usize {
+        self.super_block.cluster_size
+    }
+
+    pub(super) fn lock(&self) -> MutexGuard<'_, ()> {
+        self.mutex.lock()
+    }
+
+    pub(super) fn alloc_ino(&self) -> u64 {
+        self.next_ino.fetch_add(1, Ordering::Relaxed)
+    }
+
+    pub(super) fn find_inode(&self, position: usize) -> Option<Arc<VfatInode>> {
+        self.inodes.read().get(&position).cloned()
+    }
+
+    pub(super) fn insert_inode(&self, position: usize, inode: Arc<VfatInode>) {
+        self.inodes.write().insert(position, inode);
+    }
+
+    pub(super) fn remove_inode(&self, position: usize) -> Option<Arc<VfatInode>> {
+        self.inodes.write().remove(&position)
+    }
+
+    /// Updates the FSInfo sector of FAT32 with the allocation state.
+    fn sync_fs_info(&self) -> Result<()> {
+        if self.super_block.fat_type != FatType::Fat32 {
+            return Ok(());
+        }
+        let Some(offset) = self.super_block.fs_info_offset else {
+            return Ok(());
+        };
+
+        let mut fs_info: RawFsInfo = self.block_device.read_val(offset)?;
+        if !fs_info.is_valid() {
+            return Ok(());
+        }
+        fs_info.free_count = self.fat.free_count();
+        fs_info.next_free = if self.super_block.is_valid_cluster(self.fat.next_free()) {
+            self.fat.next_free()
+        } else {
+            FS_INFO_UNKNOWN
+        };
+        self.block_device.write_val(offset, &fs_info)?;
+        Ok(())
+    }
+}
+
+impl FileSystem for VfatFs {
+    fn sync(&self) -> Result<()> {
+        {
+            let fs_guard = self.lock();
+            let inodes: Vec<_> = self.inodes.read().values().cloned().collect();
+            for inode in inodes.iter() {
+                inode.sync_metadata(&fs_guard)?;
+            }
+        }
+
+        // Directories go last since writing back the metadata dirties their caches.
+        let inodes: Vec<_> = self.inodes.read().values().cloned().collect();
+        for inode in inodes.iter().filter(|inode| !inode.is_dir()) {
+            inode.sync_data()?;
+        }
+        for inode in inodes.iter().filter(|inode| inode.is_dir()) {
+            inode.sync_data()?;
+        }
+        self.root.sync_data()?;
+
+        self.fat.sync()?;
+        self.sync_fs_info()?;
+        self.block_device.sync()?;
+        Ok(())
+    }
+
+    fn root_inode(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+
+    fn sb(&self) -> SuperBlock {
+        SuperBlock::new(VFAT_MAGIC, self.super_block.sector_size, MAX_NAME_LEN)
+    }
+
+    fn flags(&self) -> FsFlags {
+        FsFlags::DENTRY_UNEVICTABLE
+    }
+}
+
+/// The mount options of vfat.
+#[derive(Clone, Debug)]
+pub struct VfatMountOptions {
+    pub(super) uid: u32,
+    pub(super) gid: u32,
+    /// The permission bits to be cleared from regular files.
+    pub(super) fmask: u16,
+    /// The permission bits to be cleared from directories.
+    pub(super) dmask: u16,
+}
+
+impl Default for VfatMountOptions {
+    fn default() -> Self {
+        Self {
+            uid: 0,
+            gid: 0,
+            fmask: 0o022,
+            dmask: 0o022,
+        }
+    }
+}
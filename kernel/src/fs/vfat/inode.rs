@@ -0,0 +1,1019 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::time::Duration;
+
+use aster_block::bio::BioWaiter;
+use aster_rights::Full;
+use ostd::mm::VmIo;
+
+use super::{
+    block_map::{BlockMap, ClusterLayout},
+    dentry::{
+        encode_name, find_free_slots, names_eq, DirEntry, DirEntryIter, FatAttr, RawDentry,
+        DENTRY_DELETED, DENTRY_SIZE,
+    },
+    fat::{ClusterId, FatType},
+    fs::{VfatFs, VFAT_ROOT_INO},
+    super_block::{RootDir, VfatSuperBlock},
+    utils::DosTimestamp,
+};
+use crate::{
+    fs::{
+        path::{is_dot, is_dot_or_dotdot, is_dotdot},
+        utils::{
+            CachePage, DirentVisitor, Extension, FileSystem, Inode, InodeMode, InodeType, Metadata,
+            MknodType, PageCache, PageCacheBackend,
+        },
+    },
+    prelude::*,
+    process::{Gid, Uid},
+    vm::vmo::Vmo,
+};
+
+/// The maximum size of a directory, which holds at most 65536 entries.
+const MAX_DIR_SIZE: usize = 65536 * DENTRY_SIZE;
+
+/// The maximum size of a file, limited by the 32-bit size field.
+const MAX_FILE_SIZE: usize = u32::MAX as usize;
+
+/// An inode of vfat, i.e., a file or a directory.
+///
+/// The locks must be acquired in the order of the global lock of the file system,
+/// `inner`, and `block_map`.
+pub struct VfatInode {
+    ino: u64,
+    type_: InodeType,
+    inner: RwMutex<InodeInner>,
+    /// The clusters of the inode, which is also used by the page cache backend.
+    block_map: RwMutex<BlockMap>,
+    page_cache: PageCache,
+    extension: Extension,
+    fs: Weak<VfatFs>,
+    weak_self: Weak<VfatInode>,
+}
+
+#[derive(Debug)]
+struct InodeInner {
+    /// The position of the entries in the parent directory, which is `None` for the root.
+    position: Option<DentryPosition>,
+    /// The short entry, whose size, cluster and time fields are updated on write back.
+    dentry: RawDentry,
+    size: usize,
+    atime: Duration,
+    mtime: Duration,
+    /// FAT has no change time, which is kept in memory only.
+    ctime: Duration,
+    num_subdirs: usize,
+    /// Whether `dentry` needs to be written back.
+    is_dirty: bool,
+    is_deleted: bool,
+}
+
+#[derive(Clone, Debug)]
+struct DentryPosition {
+    parent: Arc<VfatInode>,
+    /// The offset of the short entry.
+    offset: usize,
+}
+
+impl VfatInode {
+    pub(super) fn new_root(fs: Weak<VfatFs>, sb: &VfatSuperBlock) -> Arc<Self> {
+        let block_map = match sb.root_dir {
+            RootDir::Fixed { offset, size } => BlockMap::Region { offset, size },
+            RootDir::Cluster(_) => BlockMap::new_clusters(Vec::new(), Self::layout(sb)),
+        };
+        let size = block_map.size();
+        let dentry = RawDentry {
+            attr: FatAttr::DIRECTORY.bits(),
+            ..Default::default()
+        };
+
+        Arc::new_cyclic(|weak_self| Self {
+            ino: VFAT_ROOT_INO,
+            type_: InodeType::Dir,
+            inner: RwMutex::new(InodeInner {
+                position: None,
+                dentry,
+                size,
+                atime: Duration::ZERO,
+                mtime: Duration::ZERO,
+                ctime: Duration::ZERO,
+                num_subdirs: 0,
+                is_dirty: false,
+                is_deleted: false,
+            }),
+            block_map: RwMutex::new(block_map),
+            page_cache: PageCache::with_capacity(size, weak_self.clone() as _).unwrap(),
+            extension: Extension::new(),
+            fs,
+            weak_self: weak_self.clone(),
+        })
+    }
+
+    /// Loads the cluster chain of the root directory of FAT32.
+    pub(super) fn load_root_chain(&self) -> Result<()> {
+        let fs = self.fs();
+        if let RootDir::Cluster(cluster) = fs.super_block().root_dir {
+            let clusters = fs.fat().chain(cluster)?;
+            let size = {
+                let mut block_map = self.block_map.write();
+                *block_map.clusters_mut() = clusters;
+                block_map.size()
+            };
+            self.page_cache.resize(size)?;
+            self.inner.write().size = size;
+        }
+
+        let num_subdirs = self.count_subdirs()?;
+        self.inner.write().num_subdirs = num_subdirs;
+        Ok(())
+    }
+
+    fn layout(sb: &VfatSuperBlock) -> ClusterLayout {
+        ClusterLayout {
+            data_offset: sb.data_offset,
+            cluster_size: sb.cluster_size,
+        }
+    }
+
+    /// Builds an inode from a directory entry of `parent`.
+    fn from_entry(fs: &Arc<VfatFs>, parent: Arc<VfatInode>, entry: &DirEntry) -> Result<Arc<Self>> {
+        let dentry = entry.dentry;
+        let type_ = if dentry.is_dir() {
+            InodeType::Dir
+        } else {
+            InodeType::File
+        };
+
+        let mut first_cluster = dentry.first_cluster();
+        if fs.super_block().fat_type != FatType::Fat32 {
+            // The high word is used for extended attributes by OS/2 on FAT12/16.
+            first_cluster &= 0xFFFF;
+        }
+        let clusters = if first_cluster == 0 {
+            Vec::new()
+        } else {
+            fs.fat().chain(first_cluster)?
+        };
+        let block_map = BlockMap::new_clusters(clusters, Self::layout(fs.super_block()));
+
+        let size = match type_ {
+            InodeType::Dir => block_map.size(),
+            _ => dentry.size as usize,
+        };
+        if size > block_map.size() {
+            return_errno_with_message!(Errno::EIO, "the file size exceeds its clusters");
+        }
+
+        let mtime = DosTimestamp::new(dentry.write_time, dentry.write_date, 0).as_duration();
+        let atime = DosTimestamp::new(0, dentry.access_date, 0).as_duration();
+
+        let inode = Arc::new_cyclic(|weak_self| Self {
+            ino: fs.alloc_ino(),
+            type_,
+            inner: RwMutex::new(InodeInner {
+                position: Some(DentryPosition {
+                    parent,
+                    offset: entry.offset,
+                }),
+                dentry,
+                size,
+                atime,
+                mtime,
+                ctime: mtime,
+                num_subdirs: 0,
+                is_dirty: false,
+                is_deleted: false,
+            }),
+            block_map: RwMutex::new(block_map),
+            page_cache: PageCache::with_capacity(size, weak_self.clone() as _).unwrap(),
+            extension: Extension::new(),
+            fs: Arc::downgrade(fs),
+            weak_self: weak_self.clone(),
+        });
+
+        if type_ == InodeType::Dir {
+            let num_subdirs = inode.count_subdirs()?;
+            inode.inner.write().num_subdirs = num_subdirs;
+        }
+        Ok(inode)
+    }
+
+    fn fs(&self) -> Arc<VfatFs> {
+        self.fs.upgrade().unwrap()
+    }
+
+    fn self_arc(&self) -> Arc<Self> {
+        self.weak_self.upgrade().unwrap()
+    }
+
+    pub(super) fn is_dir(&self) -> bool {
+        self.type_ == InodeType::Dir
+    }
+
+    fn is_root(&self) -> bool {
+        self.ino == VFAT_ROOT_INO
+    }
+
+    /// Returns the cluster that the ".." entries of its subdirectories refer to.
+    fn dotdot_cluster(&self) -> ClusterId {
+        if self.is_root() {
+            0
+        } else {
+            self.block_map.read().first_cluster().unwrap_or(0)
+        }
+    }
+
+    /// Returns the device offset of the directory entry at `offset`, which identifies an inode.
+    fn entry_position(&self, offset: usize) -> Result<usize> {
+        let block_map = self.block_map.read();
+        match block_map.extents(offset..offset + DENTRY_SIZE).first() {
+            Some((_, range)) => Ok(range.start),
+            None => return_errno_with_message!(Errno::EIO, "invalid directory entry offset"),
+        }
+    }
+
+    fn dir_size(&self) -> usize {
+        self.block_map.read().size()
+    }
+
+    fn entries(&self) -> DirEntryIter<'_> {
+        DirEntryIter::new(self.page_cache.pages(), 0, self.dir_size())
+    }
+
+    fn count_subdirs(&self) -> Result<usize> {
+        let mut count = 0;
+        for entry in self.entries() {
+            let entry = entry?;
+            if entry.dentry.is_dir() && !entry.dentry.is_dot_or_dotdot() {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    fn find_entry(&self, name: &str) -> Result<Option<DirEntry>> {
+        for entry in self.entries() {
+            let entry = entry?;
+            if entry.dentry.is_dot_or_dotdot() {
+                continue;
+            }
+            if names_eq(&entry.name, name) || names_eq(&entry.dentry.short_name(), name) {
+                return Ok(Some(entry));
+            }
+        }
+        Ok(None)
+    }
+
+    fn is_empty_dir(&self) -> Result<bool> {
+        for entry in self.entries() {
+            if !entry?.dentry.is_dot_or_dotdot() {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Returns the opened inode of `entry`, or loads it.
+    fn get_or_load(&self, fs: &Arc<VfatFs>, entry: &DirEntry) -> Result<Arc<VfatInode>> {
+        let position = self.entry_position(entry.offset)?;
+        if let Some(inode) = fs.find_inode(position) {
+            return Ok(inode);
+        }
+        let inode = Self::from_entry(fs, self.self_arc(), entry)?;
+        fs.insert_inode(position, inode.clone());
+        Ok(inode)
+    }
+
+    /// Allocates `count` more clusters for the inode and returns the new mapped size.
+    fn extend_clusters(&self, fs: &VfatFs, count: usize) -> Result<usize> {
+        let mut block_map = self.block_map.write();
+        let prev = block_map.clusters().last().copied();
+        let clusters = fs.fat().alloc(count, prev)?;
+        block_map.clusters_mut().extend(clusters);
+        Ok(block_map.size())
+    }
+
+    /// Makes sure that there are `count` consecutive free entries, and returns the offset of them.
+    fn find_or_make_free_slots(&self, fs: &VfatFs, count: usize) -> Result<usize> {
+        let pages = self.page_cache.pages();
+        if let Some(start) = find_free_slots(pages, self.dir_size(), count)? {
+            return Ok(start);
+        }
+        if matches!(*self.block_map.read(), BlockMap::Region { .. }) {
+            return_errno_with_message!(Errno::ENOSPC, "the root directory is full");
+        }
+
+        let old_size = self.dir_size();
+        let num_clusters = (count * DENTRY_SIZE).div_ceil(fs.cluster_size());
+        if old_size + num_clusters * fs.cluster_size() > MAX_DIR_SIZE {
+            return_errno_with_message!(Errno::ENOSPC, "the directory is full");
+        }
+        let new_size = self.extend_clusters(fs, num_clusters)?;
+        self.page_cache.resize(new_size)?;
+        self.page_cache.fill_zeros(old_size..new_size)?;
+        self.inner.write().size = new_size;
+
+        match find_free_slots(pages, new_size, count)? {
+            Some(start) => Ok(start),
+            None => return_errno_with_message!(Errno::ENOSPC, "no free directory entries"),
+        }
+    }
+
+    /// Writes the entries of `name` with the short entry `dentry`, and returns their position.
+    fn add_entries(&self, fs: &VfatFs, name: &str, dentry: &RawDentry) -> Result<(usize, usize)> {
+        let mut short_names = BTreeSet::new();
+        for entry in self.entries() {
+            short_names.insert(entry?.dentry.name);
+        }
+        let entries = encode_name(name, &|short_name| short_names.contains(short_name))?;
+
+        let start = self.find_or_make_free_slots(fs, entries.len())?;
+        self.page_cache
+            .pages()
+            .write_bytes(start, &entries.to_bytes(dentry))?;
+        Ok((start, start + (entries.len() - 1) * DENTRY_SIZE))
+    }
+
+    fn delete_entries(&self, start: usize, offset: usize) -> Result<()> {
+        for entry_offset in (start..=offset).step_by(DENTRY_SIZE) {
+            self.page_cache
+                .pages()
+                .write_val(entry_offset, &DENTRY_DELETED)?;
+        }
+        Ok(())
+    }
+
+    fn touch_dir(&self) {
+        let mut inner = self.inner.write();
+        let now = DosTimestamp::now().as_duration();
+        inner.mtime = now;
+        inner.ctime = now;
+        inner.is_dirty = true;
+    }
+
+    /// Writes the short entry back to the page cache of the parent directory.
+    pub(super) fn sync_metadata(&self, _fs_guard: &MutexGuard<()>) -> Result<()> {
+        let mut inner = self.inner.write();
+        if !inner.is_dirty || inner.is_deleted {
+            return Ok(());
+        }
+        let Some(position) = inner.position.clone() else {
+            inner.is_dirty = false;
+            return Ok(());
+        };
+
+        let mut dentry = inner.dentry;
+        dentry.set_first_cluster(self.block_map.read().first_cluster().unwrap_or(0));
+        dentry.size = if self.is_dir() { 0 } else { inner.size as u32 };
+        let mtime = DosTimestamp::from_duration(inner.mtime);
+        dentry.write_time = mtime.time;
+        dentry.write_date = mtime.date;
+        dentry.access_date = DosTimestamp::from_duration(inner.atime).date;
+
+        position
+            .parent
+            .page_cache
+            .pages()
+            .write_val(position.offset, &dentry)?;
+        inner.dentry = dentry;
+        inner.is_dirty = false;
+        Ok(())
+    }
+
+    /// Resizes the file, where `inner` must be the locked inner of this inode.
+    fn resize_locked(&self, inner: &mut InodeInner, new_size: usize) -> Result<()> {
+        if new_size > MAX_FILE_SIZE {
+            return_errno!(Errno::EFBIG);
+        }
+        let fs = self.fs();
+        let cluster_size = fs.cluster_size();
+        let old_size = inner.size;
+        let num_clusters = self.block_map.read().clusters().len();
+        let new_num_clusters = new_size.div_ceil(cluster_size);
+
+        if new_size > old_size {
+            if new_num_clusters > num_clusters {
+                self.extend_clusters(&fs, new_num_clusters - num_clusters)?;
+            }
+            self.page_cache.resize(new_size)?;
+            self.page_cache.fill_zeros(old_size..new_size)?;
+        } else if new_size < old_size {
+            // Shrink the page cache first, so that the dirty pages are written back to the
+            // clusters while they are still owned by the file.
+            self.page_cache.resize(new_size)?;
+            if new_num_clusters < num_clusters {
+                let (freed, new_last) = {
+                    let mut block_map = self.block_map.write();
+                    let freed = block_map.clusters_mut().split_off(new_num_clusters);
+                    (freed, block_map.clusters().last().copied())
+                };
+                fs.fat().free(&freed, new_last)?;
+            }
+        }
+
+        inner.size = new_size;
+        inner.is_dirty = true;
+        Ok(())
+    }
+
+    fn make_mode(&self, attr: FatAttr) -> InodeMode {
+        let options = self.fs().mount_options().clone();
+        let mut mode = InodeMode::from_bits_truncate(0o777);
+        if self.is_dir() {
+            mode.remove(InodeMode::from_bits_truncate(options.dmask));
+        } else {
+            mode.remove(InodeMode::from_bits_truncate(options.fmask));
+            if attr.contains(FatAttr::READ_ONLY) {
+                mode.remove(InodeMode::S_IWUSR | InodeMode::S_IWGRP | InodeMode::S_IWOTH);
+            }
+        }
+        mode
+    }
+
+    /// Returns whether `self` is `inode` or one of its descendants.
+    fn is_descendant_of(&self, inode: &VfatInode) -> bool {
+        let mut current = self.self_arc();
+        loop {
+            if current.ino == inode.ino {
+                return true;
+            }
+            let parent = match current.inner.read().position.as_ref() {
+                Some(position) => position.parent.clone(),
+                None => return false,
+            };
+            current = parent;
+        }
+    }
+
+    fn create_entry(&self, name: &str, type_: InodeType) -> Result<Arc<VfatInode>> {
+        if !self.is_dir() {
+            return_errno!(Errno::ENOTDIR);
+        }
+        if type_ != InodeType::File && type_ != InodeType::Dir {
+            return_errno_with_message!(Errno::EPERM, "unsupported inode type");
+        }
+
+        let fs = self.fs();
+        let _fs_guard = fs.lock();
+        if self.inner.read().is_deleted {
+            return_errno!(Errno::ENOENT);
+        }
+        if self.find_entry(name)?.is_some() {
+            return_errno!(Errno::EEXIST);
+        }
+
+        let now = DosTimestamp::now();
+        let mut dentry = RawDentry {
+            attr: if type_ == InodeType::Dir {
+                FatAttr::DIRECTORY.bits()
+            } else {
+                FatAttr::ARCHIVE.bits()
+            },
+            create_time_tenth: now.increment_10ms,
+            create_time: now.time,
+            create_date: now.date,
+            access_date: now.date,
+            write_time: now.time,
+            write_date: now.date,
+            ..Default::default()
+        };
+
+        let cluster = if type_ == InodeType::Dir {
+            let cluster = fs.fat().alloc(1, None)?[0];
+            dentry.set_first_cluster(cluster);
+            Some(cluster)
+        } else {
+            None
+        };
+
+        let (start, offset) = match self.add_entries(&fs, name, &dentry) {
+            Ok(position) => position,
+            Err(err) => {
+                if let Some(cluster) = cluster {
+                    fs.fat().free(&[cluster], None)?;
+                }
+                return Err(err);
+            }
+        };
+        let entry = DirEntry {
+            name: name.to_string(),
+            dentry,
+            start,
+            offset,
+        };
+        let inode = match type_ {
+            InodeType::Dir => self.init_dir_entry(&fs, &entry)?,
+            _ => self.get_or_load(&fs, &entry)?,
+        };
+
+        self.touch_dir();
+        if type_ == InodeType::Dir {
+            self.inner.write().num_subdirs += 1;
+        }
+        Ok(inode)
+    }
+
+    /// Writes the "." and ".." entries of a new directory and loads it.
+    fn init_dir_entry(&self, fs: &Arc<VfatFs>, entry: &DirEntry) -> Result<Arc<VfatInode>> {
+        let cluster = entry.dentry.first_cluster();
+        let mut buf = vec![0u8; fs.cluster_size()];
+
+        let mut dot = entry.dentry;
+        dot.name = *b".          ";
+        buf[..DENTRY_SIZE].copy_from_slice(dot.as_bytes());
+        let mut dotdot = entry.dentry;
+        dotdot.name = *b"..         ";
+        dotdot.set_first_cluster(self.dotdot_cluster());
+        buf[DENTRY_SIZE..2 * DENTRY_SIZE].copy_from_slice(dotdot.as_bytes());
+
+        // Write the cluster through the device since the inode is loaded from it.
+        fs.block_device()
+            .write_bytes(fs.super_block().cluster_offset(cluster), &buf)?;
+        self.get_or_load(fs, entry)
+    }
+
+    fn remove_entry(&self, name: &str, is_dir: bool) -> Result<()> {
+        if !self.is_dir() {
+            return_errno!(Errno::ENOTDIR);
+        }
+        if is_dot(name) {
+            return_errno_with_message!(Errno::EINVAL, "rmdir on .");
+        }
+        if is_dotdot(name) {
+            return_errno_with_message!(Errno::ENOTEMPTY, "rmdir on ..");
+        }
+
+        let fs = self.fs();
+        let _fs_guard = fs.lock();
+        let Some(entry) = self.find_entry(name)? else {
+            return_errno!(Errno::ENOENT);
+        };
+        let inode = self.get_or_load(&fs, &entry)?;
+        match (is_dir, inode.is_dir()) {
+            (true, false) => return_errno!(Errno::ENOTDIR),
+            (false, true) => return_errno!(Errno::EISDIR),
+            _ => {}
+        }
+        if is_dir && !inode.is_empty_dir()? {
+            return_errno!(Errno::ENOTEMPTY);
+        }
+
+        self.delete_entries(entry.start, entry.offset)?;
+        fs.remove_inode(self.entry_position(entry.offset)?);
+        inode.inner.write().is_deleted = true;
+
+        self.touch_dir();
+        if is_dir {
+            self.inner.write().num_subdirs -= 1;
+        }
+        Ok(())
+    }
+
+    fn rename_entry(&self, old_name: &str, target: &VfatInode, new_name: &str) -> Result<()> {
+        if !self.is_dir() || !target.is_dir() {
+            return_errno!(Errno::ENOTDIR);
+        }
+
+        let fs = self.fs();
+        let fs_guard = fs.lock();
+        let Some(old_entry) = self.find_entry(old_name)? else {
+            return_errno!(Errno::ENOENT);
+        };
+        let inode = self.get_or_load(&fs, &old_entry)?;
+        if inode.is_dir() && target.is_descendant_of(&inode) {
+            return_errno_with_message!(Errno::EINVAL, "cannot move a directory into itself");
+        }
+
+        if let Some(new_entry) = target.find_entry(new_name)? {
+            let is_same_entry = self.ino == target.ino && new_entry.offset == old_entry.offset;
+            if is_same_entry && old_entry.name == new_name {
+                return Ok(());
+            }
+            if !is_same_entry {
+                let victim = target.get_or_load(&fs, &new_entry)?;
+                match (inode.is_dir(), victim.is_dir()) {
+                    (true, false) => return_errno!(Errno::ENOTDIR),
+                    (false, true) => return_errno!(Errno::EISDIR),
+                    (true, true) if !victim.is_empty_dir()? => return_errno!(Errno::ENOTEMPTY),
+                    _ => {}
+                }
+                target.delete_entries(new_entry.start, new_entry.offset)?;
+                fs.remove_inode(target.entry_position(new_entry.offset)?);
+                victim.inner.write().is_deleted = true;
+                if victim.is_dir() {
+                    target.inner.write().num_subdirs -= 1;
+                }
+            }
+        }
+
+        // Write the up-to-date short entry to the new place before removing the old one.
+        inode.inner.write().is_dirty = true;
+        inode.sync_metadata(&fs_guard)?;
+        let dentry = inode.inner.read().dentry;
+        let (_, offset) = target.add_entries(&fs, new_name, &dentry)?;
+        self.delete_entries(old_entry.start, old_entry.offset)?;
+
+        fs.remove_inode(self.entry_position(old_entry.offset)?);
+        fs.insert_inode(target.entry_position(offset)?, inode.clone());
+        inode.inner.write().position = Some(DentryPosition {
+            parent: target.self_arc(),
+            offset,
+        });
+
+        if self.ino != target.ino {
+            if inode.is_dir() {
+                let mut dotdot: RawDentry = inode.page_cache.pages().read_val(DENTRY_SIZE)?;
+                if dotdot.name == *b"..         " {
+                    dotdot.set_first_cluster(target.dotdot_cluster());
+                    inode.page_cache.pages().write_val(DENTRY_SIZE, &dotdot)?;
+                }
+                self.inner.write().num_subdirs -= 1;
+                target.inner.write().num_subdirs += 1;
+            }
+            target.touch_dir();
+        }
+        self.touch_dir();
+        Ok(())
+    }
+}
+
+impl PageCacheBackend for VfatInode {
+    fn read_page_async(&self, idx: usize, frame: &CachePage) -> Result<BioWaiter> {
+        let fs = self.fs();
+        self.block_map
+            .read()
+            .read_page(fs.block_device(), idx, frame)
+    }
+
+    fn write_page_async(&self, idx: usize, frame: &CachePage) -> Result<BioWaiter> {
+        let fs = self.fs();
+        self.block_map
+            .read()
+            .write_page(fs.block_device(), idx, frame)
+    }
+
+    fn npages(&self) -> usize {
+        self.block_map.read().size().div_ceil(PAGE_SIZE)
+    }
+
+    fn plug(&self) {
+        self.fs().block_device().plug();
+    }
+
+    fn unplug(&self) {
+        self.fs().block_device().unplug();
+    }
+}
+
+impl Inode for VfatInode {
+    fn size(&self) -> usize {
+        self.inner.read().size
+    }
+
+    fn resize(&self, new_size: usize) -> Result<()> {
+        if self.is_dir() {
+            return_errno!(Errno::EISDIR);
+        }
+        let mut inner = self.inner.write();
+        self.resize_locked(&mut inner, new_size)?;
+        let now = DosTimestamp::now().as_duration();
+        inner.mtime = now;
+        inner.ctime = now;
+        Ok(())
+    }
+
+    fn metadata(&self) -> Metadata {
+        let inner = self.inner.read();
+        let fs = self.fs();
+        let blk_size = fs.super_block().sector_size;
+        let nlinks = if self.is_dir() {
+            inner.num_subdirs + 2
+        } else {
+            1
+        };
+        // The root directory has no entry to record the creation time.
+        let btime = inner.position.as_ref().map(|_| {
+            let dentry = &inner.dentry;
+            DosTimestamp::new(
+                dentry.create_time,
+                dentry.create_date,
+                dentry.create_time_tenth,
+            )
+            .as_duration()
+        });
+
+        Metadata {
+            dev: 0,
+            ino: self.ino,
+            size: inner.size,
+            blk_size,
+            blocks: self.block_map.read().size() / 512,
+            atime: inner.atime,
+            mtime: inner.mtime,
+            ctime: inner.ctime,
+            btime,
+            type_: self.type_,
+            mode: self.make_mode(inner.dentry.attr()),
+            nlinks,
+            uid: Uid::new(fs.mount_options().uid),
+            gid: Gid::new(fs.mount_options().gid),
+            rdev: 0,
+        }
+    }
+
+    fn ino(&self) -> u64 {
+        self.ino
+    }
+
+    fn type_(&self) -> InodeType {
+        self.type_
+    }
+
+    fn mode(&self) -> Result<InodeMode> {
+        Ok(self.make_mode(self.inner.read().dentry.attr()))
+    }
+
+    fn set_mode(&self, mode: InodeMode) -> Result<()> {
+        // Only the read-only attribute of files can be kept.
+        if self.is_dir() {
+            return Ok(());
+        }
+        let mut inner = self.inner.write();
+        let mut attr = inner.dentry.attr();
+        attr.set(FatAttr::READ_ONLY, !mode.contains(InodeMode::S_IWUSR));
+        inner.dentry.attr = attr.bits();
+        inner.ctime = DosTimestamp::now().as_duration();
+        inner.is_dirty = true;
+        Ok(())
+    }
+
+    fn owner(&self) -> Result<Uid> {
+        Ok(Uid::new(self.fs().mount_options().uid))
+    }
+
+    fn set_owner(&self, uid: Uid) -> Result<()> {
+        if uid != self.owner()? {
+            return_errno_with_message!(Errno::EPERM, "vfat does not support ownership");
+        }
+        Ok(())
+    }
+
+    fn group(&self) -> Result<Gid> {
+        Ok(Gid::new(self.fs().mount_options().gid))
+    }
+
+    fn set_group(&self, gid: Gid) -> Result<()> {
+        if gid != self.group()? {
+            return_errno_with_message!(Errno::EPERM, "vfat does not support ownership");
+        }
+        Ok(())
+    }
+
+    fn atime(&self) -> Duration {
+        self.inner.read().atime
+    }
+
+    fn set_atime(&self, time: Duration) {
+        let mut inner = self.inner.write();
+        inner.atime = time;
+        inner.is_dirty = true;
+    }
+
+    fn mtime(&self) -> Duration {
+        self.inner.read().mtime
+    }
+
+    fn set_mtime(&self, time: Duration) {
+        let mut inner = self.inner.write();
+        inner.mtime = time;
+        inner.is_dirty = true;
+    }
+
+    fn ctime(&self) -> Duration {
+        self.inner.read().ctime
+    }
+
+    fn set_ctime(&self, time: Duration) {
+        self.inner.write().ctime = time;
+    }
+
+    fn page_cache(&self) -> Option<Vmo<Full>> {
+        Some(self.page_cache.pages().dup())
+    }
+
+    fn read_at(&self, offset: usize, writer: &mut VmWriter) -> Result<usize> {
+        if self.is_dir() {
+            return_errno!(Errno::EISDIR);
+        }
+        let inner = self.inner.read();
+        let (offset, read_len) = {
+            let start = inner.size.min(offset);
+            let end = inner.size.min(offset + writer.avail());
+            (start, end - start)
+        };
+        self.page_cache.pages().read(offset, writer)?;
+        Ok(read_len)
+    }
+
+    fn read_direct_at(&self, offset: usize, writer: &mut VmWriter) -> Result<usize> {
+        // The page cache also serves direct I/O, as the clusters may not be page-aligned.
+        self.read_at(offset, writer)
+    }
+
+    fn write_at(&self, offset: usize, reader: &mut VmReader) -> Result<usize> {
+        if self.is_dir() {
+            return_errno!(Errno::EISDIR);
+        }
+        let write_len = reader.remain();
+        let end = offset
+            .checked_add(write_len)
+            .ok_or(Error::new(Errno::EFBIG))?;
+
+        let mut inner = self.inner.write();
+        if end > inner.size {
+            self.resize_locked(&mut inner, end)?;
+        }
+        self.page_cache.pages().write(offset, reader)?;
+
+        let now = DosTimestamp::now().as_duration();
+        inner.mtime = now;
+        inner.ctime = now;
+        inner.dentry.attr |= FatAttr::ARCHIVE.bits();
+        inner.is_dirty = true;
+        Ok(write_len)
+    }
+
+    fn write_direct_at(&self, offset: usize, reader: &mut VmReader) -> Result<usize> {
+        self.write_at(offset, reader)
+    }
+
+    fn create(&self, name: &str, type_: InodeType, _mode: InodeMode) -> Result<Arc<dyn Inode>> {
+        Ok(self.create_entry(name, type_)?)
+    }
+
+    fn mknod(&self, _name: &str, _mode: InodeMode, _type_: MknodType) -> Result<Arc<dyn Inode>> {
+        return_errno_with_message!(Errno::EPERM, "vfat does not support special files");
+    }
+
+    fn readdir_at(&self, offset: usize, visitor: &mut dyn DirentVisitor) -> Result<usize> {
+        if !self.is_dir() {
+            return_errno!(Errno::ENOTDIR);
+        }
+
+        let fs = self.fs();
+        let _fs_guard = fs.lock();
+        let parent_ino = match self.inner.read().position.as_ref() {
+            Some(position) => position.parent.ino,
+            None => self.ino,
+        };
+
+        // The offsets 0 and 1 stand for "." and "..", and the others stand for the
+        // byte offsets of the entries plus 2.
+        let try_readdir = |offset: &mut usize, visitor: &mut dyn DirentVisitor| -> Result<()> {
+            if *offset == 0 {
+                visitor.visit(".", self.ino, InodeType::Dir, 1)?;
+                *offset = 1;
+            }
+            if *offset == 1 {
+                visitor.visit("..", parent_ino, InodeType::Dir, 2)?;
+                *offset = 2;
+            }
+
+            let mut iter = DirEntryIter::new(self.page_cache.pages(), *offset - 2, self.dir_size());
+            while let Some(entry) = iter.next() {
+                let entry = entry?;
+                if !entry.dentry.is_dot_or_dotdot() {
+                    let inode = self.get_or_load(&fs, &entry)?;
+                    visitor.visit(&entry.name, inode.ino, inode.type_, iter.offset() + 2)?;
+                }
+                *offset = iter.offset() + 2;
+            }
+            Ok(())
+        };
+
+        let mut iterate_offset = offset;
+        match try_readdir(&mut iterate_offset, visitor) {
+            Err(e) if iterate_offset == offset => Err(e),
+            _ => Ok(iterate_offset - offset),
+        }
+    }
+
+    fn link(&self, _old: &Arc<dyn Inode>, _name: &str) -> Result<()> {
+        return_errno_with_message!(Errno::EPERM, "vfat does not support hard links");
+    }
+
+    fn unlink(&self, name: &str) -> Result<()> {
+        if is_dot_or_dotdot(name) {
+            return_errno!(Errno::EISDIR);
+        }
+        self.remove_entry(name, false)
+    }
+
+    fn rmdir(&self, name: &str) -> Result<()> {
+        self.remove_entry(name, true)
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>> {
+        if !self.is_dir() {
+            return_errno!(Errno::ENOTDIR);
+        }
+        if is_dot(name) {
+            return Ok(self.self_arc());
+        }
+        if is_dotdot(name) {
+            return Ok(match self.inner.read().position.as_ref() {
+                Some(position) => position.parent.clone(),
+                None => self.self_arc(),
+            });
+        }
+
+        let fs = self.fs();
+        let _fs_guard = fs.lock();
+        let Some(entry) = self.find_entry(name)? else {
+            return_errno!(Errno::ENOENT);
+        };
+        Ok(self.get_or_load(&fs, &entry)?)
+    }
+
+    fn rename(&self, old_name: &str, target: &Arc<dyn Inode>, new_name: &str) -> Result<()> {
+        if is_dot_or_dotdot(old_name) || is_dot_or_dotdot(new_name) {
+            return_errno!(Errno::EISDIR);
+        }
+        let Some(target) = target.downcast_ref::<VfatInode>() else {
+            return_errno_with_message!(Errno::EXDEV, "not a vfat inode");
+        };
+        self.rename_entry(old_name, target, new_name)
+    }
+
+    fn read_link(&self) -> Result<String> {
+        return_errno_with_message!(Errno::EINVAL, "vfat does not support symbolic links");
+    }
+
+    fn write_link(&self, _target: &str) -> Result<()> {
+        return_errno_with_message!(Errno::EPERM, "vfat does not support symbolic links");
+    }
+
+    fn sync_all(&self) -> Result<()> {
+        let fs = self.fs();
+        let parent = {
+            let fs_guard = fs.lock();
+            self.sync_metadata(&fs_guard)?;
+            self.inner
+                .read()
+                .position
+                .as_ref()
+                .map(|position| position.parent.clone())
+        };
+
+        self.sync_data()?;
+        if let Some(parent) = parent {
+            parent.sync_data()?;
+        }
+        fs.fat().sync()?;
+        fs.block_device().sync()?;
+        Ok(())
+    }
+
+    fn sync_data(&self) -> Result<()> {
+        let size = self.page_cache.pages().size();
+        self.page_cache.evict_range(0..size)
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        self.fs()
+    }
+
+    fn extension(&self) -> Option<&Extension> {
+        Some(&self.extension)
+    }
+}
+
+impl Drop for VfatInode {
+    fn drop(&mut self) {
+        if !self.inner.get_mut().is_deleted {
+            return;
+        }
+        // Reclaim the clusters of the inode, which is no longer referenced by any entry.
+        let Some(fs) = self.fs.upgrade() else {
+            return;
+        };
+        let clusters = self.block_map.get_mut().clusters().to_vec();
+        if let Err(err) = fs.fat().free(&clusters, None) {
+            warn!(
+                "failed to free the clusters of a deleted vfat inode: {:?}",
+                err
+            );
+        }
+    }
+}
+
+impl Debug for VfatInode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("VfatInode")
+            .field("ino", &self.ino)
+            .field("type_", &self.type_)
+            .field("block_map", &self.block_map)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,21 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The vfat file system, i.e., FAT12, FAT16 and FAT32 with long file names.
+//!
+//! FAT volumes are common as the boot media of RISC-V boards and as EFI system
+//! partitions, so the files of the firmware and the bootloader can be read and
+//! written through this driver. ExFAT volumes are handled by the `exfat` module.
+//!
+//! Files and directories are supported, while FAT has no room for ownership,
+//! permission bits other than read-only, hard links, symbolic links or special files.
+
+mod block_map;
+mod dentry;
+mod fat;
+mod fs;
+mod inode;
+mod super_block;
+mod utils;
+
+pub use fs::{VfatFs, VfatMountOptions};
+pub use inode::VfatInode;
@@ -0,0 +1,255 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::ops::Range;
+
+use ostd::Pod;
+
+use super::fat::{ClusterId, FatType, FIRST_DATA_CLUSTER};
+use crate::prelude::*;
+
+pub(super) const BOOT_SIGNATURE: u16 = 0xAA55;
+
+const FS_INFO_LEAD_SIGNATURE: u32 = 0x4161_5252;
+const FS_INFO_STRUCT_SIGNATURE: u32 = 0x6141_7272;
+const FS_INFO_TRAIL_SIGNATURE: u32 = 0xAA55_0000;
+
+/// The value of an FSInfo field whose content is unknown.
+pub(super) const FS_INFO_UNKNOWN: u32 = 0xFFFF_FFFF;
+
+/// The BIOS parameter block shared by all FAT variants, as laid out in the boot sector.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Pod)]
+#[expect(dead_code)]
+pub(super) struct RawBootSector {
+    pub jump_boot: [u8; 3],
+    pub oem_name: [u8; 8],
+    pub bytes_per_sector: u16,
+    pub sectors_per_cluster: u8,
+    pub reserved_sectors: u16,
+    pub num_fats: u8,
+    pub root_entries: u16,
+    pub total_sectors_16: u16,
+    pub media: u8,
+    pub fat_size_16: u16,
+    pub sectors_per_track: u16,
+    pub num_heads: u16,
+    pub hidden_sectors: u32,
+    pub total_sectors_32: u32,
+    /// The extended BPB, whose layout differs between FAT12/16 and FAT32.
+    pub extended: [u8; 54],
+    pub boot_code: [u8; 420],
+    pub signature: u16,
+}
+
+/// The extended BPB of FAT32.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Pod)]
+#[expect(dead_code)]
+struct RawFat32Extension {
+    fat_size_32: u32,
+    ext_flags: u16,
+    fs_version: u16,
+    root_cluster: u32,
+    fs_info: u16,
+    backup_boot_sector: u16,
+    reserved: [u8; 12],
+    drive_number: u8,
+    reserved1: u8,
+    boot_signature: u8,
+    volume_id: u32,
+    volume_label: [u8; 11],
+    fs_type: [u8; 8],
+}
+
+/// The FSInfo sector of FAT32, which caches the allocation state of the volume.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Pod)]
+#[expect(dead_code)]
+pub(super) struct RawFsInfo {
+    lead_signature: u32,
+    reserved: [u8; 480],
+    struct_signature: u32,
+    pub free_count: u32,
+    pub next_free: u32,
+    reserved1: [u8; 12],
+    trail_signature: u32,
+}
+
+impl RawFsInfo {
+    pub fn is_valid(&self) -> bool {
+        self.lead_signature == FS_INFO_LEAD_SIGNATURE
+            && self.struct_signature == FS_INFO_STRUCT_SIGNATURE
+            && self.trail_signature == FS_INFO_TRAIL_SIGNATURE
+    }
+}
+
+/// Where the root directory lives.
+#[derive(Clone, Copy, Debug)]
+pub(super) enum RootDir {
+    /// The fixed-size root directory region of FAT12/16.
+    Fixed { offset: usize, size: usize },
+    /// The cluster chain of the root directory of FAT32.
+    Cluster(ClusterId),
+}
+
+/// The in-memory super block, with every size and offset in bytes.
+#[derive(Clone, Copy, Debug)]
+pub(super) struct VfatSuperBlock {
+    pub fat_type: FatType,
+    pub sector_size: usize,
+    pub cluster_size: usize,
+    /// The device offset of the first FAT.
+    pub fat_offset: usize,
+    /// The size of each FAT.
+    pub fat_size: usize,
+    pub num_fats: usize,
+    /// The only FAT to be used, if mirroring is disabled.
+    pub active_fat: Option<usize>,
+    pub root_dir: RootDir,
+    /// The device offset of the first data cluster.
+    pub data_offset: usize,
+    /// The number of data clusters.
+    pub num_clusters: u32,
+    /// The device offset of the FSInfo sector.
+    pub fs_info_offset: Option<usize>,
+}
+
+impl TryFrom<RawBootSector> for VfatSuperBlock {
+    type Error = crate::error::Error;
+
+    fn try_from(raw: RawBootSector) -> Result<Self> {
+        if raw.signature != BOOT_SIGNATURE {
+            return_errno_with_message!(Errno::EINVAL, "invalid boot sector signature");
+        }
+
+        let sector_size = raw.bytes_per_sector as usize;
+        if !sector_size.is_power_of_two() || !(512..=4096).contains(&sector_size) {
+            return_errno_with_message!(Errno::EINVAL, "invalid sector size");
+        }
+        let sectors_per_cluster = raw.sectors_per_cluster as usize;
+        if !sectors_per_cluster.is_power_of_two() {
+            return_errno_with_message!(Errno::EINVAL, "invalid cluster size");
+        }
+        if raw.reserved_sectors == 0 || raw.num_fats == 0 {
+            return_errno_with_message!(Errno::EINVAL, "invalid FAT layout");
+        }
+
+        let fat32_ext = RawFat32Extension::from_bytes(&raw.extended);
+        let fat_sectors = if raw.fat_size_16 != 0 {
+            raw.fat_size_16 as usize
+        } else {
+            fat32_ext.fat_size_32 as usize
+        };
+        let total_sectors = if raw.total_sectors_16 != 0 {
+            raw.total_sectors_16 as usize
+        } else {
+            raw.total_sectors_32 as usize
+        };
+
+        let root_size = raw.root_entries as usize * super::dentry::DENTRY_SIZE;
+        let root_sectors = root_size.div_ceil(sector_size);
+        let data_sector =
+            raw.reserved_sectors as usize + raw.num_fats as usize * fat_sectors + root_sectors;
+        if fat_sectors == 0 || data_sector >= total_sectors {
+            return_errno_with_message!(Errno::EINVAL, "invalid FAT layout");
+        }
+        let num_clusters = (total_sectors - data_sector) / sectors_per_cluster;
+
+        // The FAT type is determined by the number of clusters only.
+        let fat_type = if num_clusters < 4085 {
+            FatType::Fat12
+        } else if num_clusters < 65525 {
+            FatType::Fat16
+        } else {
+            FatType::Fat32
+        };
+
+        let fat_offset = raw.reserved_sectors as usize * sector_size;
+        let fat_size = fat_sectors * sector_size;
+        if fat_type.fat_bytes(num_clusters as usize + FIRST_DATA_CLUSTER as usize) > fat_size {
+            return_errno_with_message!(Errno::EINVAL, "the FAT is too small");
+        }
+
+        let (root_dir, active_fat, fs_info_offset) = if fat_type == FatType::Fat32 {
+            if raw.root_entries != 0 || fat32_ext.fs_version != 0 {
+                return_errno_with_message!(Errno::EINVAL, "invalid FAT32 boot sector");
+            }
+            // Bit 7 of the flags disables mirroring, and bits 0-3 select the active FAT.
+            let active_fat = if fat32_ext.ext_flags & 0x80 != 0 {
+                let active_fat = (fat32_ext.ext_flags & 0xF) as usize;
+                if active_fat >= raw.num_fats as usize {
+                    return_errno_with_message!(Errno::EINVAL, "invalid active FAT");
+                }
+                Some(active_fat)
+            } else {
+                None
+            };
+            let fs_info_offset = match fat32_ext.fs_info {
+                0 | 0xFFFF => None,
+                sector => Some(sector as usize * sector_size),
+            };
+            (
+                RootDir::Cluster(fat32_ext.root_cluster),
+                active_fat,
+                fs_info_offset,
+            )
+        } else {
+            if raw.root_entries == 0 {
+                return_errno_with_message!(Errno::EINVAL, "invalid FAT12/16 root directory");
+            }
+            (
+                RootDir::Fixed {
+                    offset: fat_offset + raw.num_fats as usize * fat_size,
+                    size: root_size,
+                },
+                None,
+                None,
+            )
+        };
+
+        Ok(Self {
+            fat_type,
+            sector_size,
+            cluster_size: sectors_per_cluster * sector_size,
+            fat_offset,
+            fat_size,
+            num_fats: raw.num_fats as usize,
+            active_fat,
+            root_dir,
+            data_offset: data_sector * sector_size,
+            num_clusters: num_clusters as u32,
+            fs_info_offset,
+        })
+    }
+}
+
+impl VfatSuperBlock {
+    /// Returns the device offset of the FAT copy at `index`.
+    pub fn fat_copy_offset(&self, index: usize) -> usize {
+        self.fat_offset + index * self.fat_size
+    }
+
+    /// Returns the indices of the FAT copies that must be updated on a write.
+    pub fn fat_copies(&self) -> Range<usize> {
+        match self.active_fat {
+            Some(index) => index..index + 1,
+            None => 0..self.num_fats,
+        }
+    }
+
+    /// Returns the index of the FAT copy to read from.
+    pub fn read_fat(&self) -> usize {
+        self.active_fat.unwrap_or(0)
+    }
+
+    /// Returns the device offset of cluster `cluster`.
+    pub fn cluster_offset(&self, cluster: ClusterId) -> usize {
+        debug_assert!(cluster >= FIRST_DATA_CLUSTER);
+        self.data_offset + (cluster - FIRST_DATA_CLUSTER) as usize * self.cluster_size
+    }
+
+    /// Returns whether `cluster` is a valid data cluster.
+    pub fn is_valid_cluster(&self, cluster: ClusterId) -> bool {
+        (FIRST_DATA_CLUSTER..FIRST_DATA_CLUSTER + self.num_clusters).contains(&cluster)
+    }
+}
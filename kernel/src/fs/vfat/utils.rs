@@ -0,0 +1,120 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::time::Duration;
+
+use time::{Date, Month, OffsetDateTime, PrimitiveDateTime, Time};
+
+/// A timestamp in the format of FAT directory entries, which is taken as UTC.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(super) struct DosTimestamp {
+    /// The time at the precision of two seconds.
+    pub time: u16,
+    pub date: u16,
+    /// The remainder in 10ms, ranging from 0 to 199.
+    pub increment_10ms: u8,
+}
+
+/// The range that a timestamp is able to represent, from 1980-01-01 to 2107-12-31.
+const MIN_SECS: u64 = 315_532_800;
+const MAX_SECS: u64 = 4_354_819_198;
+
+impl DosTimestamp {
+    pub fn now() -> Self {
+        #[cfg(not(ktest))]
+        {
+            use crate::time::clocks::RealTimeClock;
+            Self::from_duration(RealTimeClock::get().read_time())
+        }
+
+        // When ktesting, the time module has not been initialized yet, return a fake value instead.
+        #[cfg(ktest)]
+        {
+            Self::from_duration(Duration::ZERO)
+        }
+    }
+
+    pub fn new(time: u16, date: u16, increment_10ms: u8) -> Self {
+        Self {
+            time,
+            date,
+            increment_10ms,
+        }
+    }
+
+    /// Converts the time since the UNIX epoch, clamping it into the representable range.
+    pub fn from_duration(duration: Duration) -> Self {
+        let duration = if duration.as_secs() < MIN_SECS {
+            Duration::from_secs(MIN_SECS)
+        } else if duration.as_secs() > MAX_SECS {
+            Duration::from_secs(MAX_SECS)
+        } else {
+            duration
+        };
+        let date_time = OffsetDateTime::from_unix_timestamp(duration.as_secs() as i64).unwrap();
+
+        let time = ((date_time.hour() as u16) << 11)
+            | ((date_time.minute() as u16) << 5)
+            | ((date_time.second() as u16) >> 1);
+        let date = (((date_time.year() - 1980) as u16) << 9)
+            | ((date_time.month() as u16) << 5)
+            | (date_time.day() as u16);
+        let increment_10ms =
+            ((date_time.second() % 2) as u32 * 100 + duration.subsec_millis() / 10) as u8;
+
+        Self {
+            time,
+            date,
+            increment_10ms,
+        }
+    }
+
+    /// Converts to the time since the UNIX epoch. An invalid timestamp is converted to zero.
+    pub fn as_duration(&self) -> Duration {
+        let date = Month::try_from(((self.date >> 5) & 0xF) as u8)
+            .ok()
+            .and_then(|month| {
+                Date::from_calendar_date(
+                    1980 + (self.date >> 9) as i32,
+                    month,
+                    (self.date & 0x1F) as u8,
+                )
+                .ok()
+            });
+        let time = Time::from_hms(
+            (self.time >> 11) as u8,
+            ((self.time >> 5) & 0x3F) as u8,
+            ((self.time & 0x1F) * 2) as u8,
+        )
+        .ok();
+        let (Some(date), Some(time)) = (date, time) else {
+            return Duration::ZERO;
+        };
+
+        let secs = PrimitiveDateTime::new(date, time)
+            .assume_utc()
+            .unix_timestamp() as u64;
+        let increment_10ms = self.increment_10ms.min(199) as u64;
+        Duration::from_secs(secs) + Duration::from_millis(increment_10ms * 10)
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn timestamp_round_trip() {
+        // 2024-02-29 13:37:41.250 UTC
+        let duration = Duration::from_millis(1_709_213_861_250);
+        let timestamp = DosTimestamp::from_duration(duration);
+        assert_eq!(timestamp.as_duration(), duration);
+    }
+
+    #[ktest]
+    fn timestamp_clamping() {
+        let timestamp = DosTimestamp::from_duration(Duration::ZERO);
+        assert_eq!(timestamp.as_duration(), Duration::from_secs(MIN_SECS));
+    }
+}
@@ -0,0 +1,144 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The IDs of System V IPC objects.
+
+use alloc::collections::btree_map::BTreeMap;
+
+use id_alloc::IdAlloc;
+use ostd::sync::{PreemptDisabled, RwLockReadGuard};
+
+use super::{key_t, IpcFlags, IpcPermission};
+use crate::prelude::*;
+
+/// The key that always creates a new IPC object.
+pub const IPC_PRIVATE: key_t = 0;
+
+/// A System V IPC object.
+pub trait IpcObject {
+    /// Returns the permission of the object, which also records its key.
+    fn permission(&self) -> &IpcPermission;
+}
+
+/// The IPC objects of the same kind, indexed by their IDs.
+///
+/// The ID of an object is allocated when it is created. Unless the object is created with
+/// [`IPC_PRIVATE`], the object can also be found with its key.
+//
+// TODO: Support IPC namespaces. Now all the IPC objects are global.
+pub struct IpcIds<T> {
+    objects: RwLock<BTreeMap<key_t, Arc<T>>>,
+    id_allocator: SpinLock<IdAlloc>,
+}
+
+impl<T: IpcObject> IpcIds<T> {
+    /// Creates an empty set of the IPC objects, which can hold at most `max_objects` objects.
+    pub fn new(max_objects: usize) -> Self {
+        let mut id_allocator = IdAlloc::with_capacity(max_objects + 1);
+        // Remove the first index 0
+        id_allocator.alloc();
+
+        Self {
+            objects: RwLock::new(BTreeMap::new()),
+            id_allocator: SpinLock::new(id_allocator),
+        }
+    }
+
+    /// Gets the ID of the object with `key`, and creates the object if necessary.
+    ///
+    /// If the object exists, it is checked with `check`. Otherwise, if `flags` allows, a new
+    /// object is created by `new_object` with the allocated ID.
+    pub fn get_or_create<C, F>(
+        &self,
+        key: key_t,
+        flags: IpcFlags,
+        check: C,
+        new_object: F,
+    ) -> Result<key_t>
+    where
+        C: FnOnce(&T) -> Result<()>,
+        F: FnOnce(key_t) -> Result<T>,
+    {
+        let mut objects = self.objects.write();
+
+        if key != IPC_PRIVATE {
+            let object = objects
+                .iter()
+                .find(|(_, object)| object.permission().key() == key);
+            if let Some((id, object)) = object {
+                if flags.contains(IpcFlags::IPC_CREAT | IpcFlags::IPC_EXCL) {
+                    return_errno_with_message!(Errno::EEXIST, "the IPC key already exists");
+                }
+                check(object)?;
+                return Ok(*id);
+            }
+
+            if !flags.contains(IpcFlags::IPC_CREAT) {
+                return_errno_with_message!(Errno::ENOENT, "the IPC key does not exist");
+            }
+        }
+
+        let id = self
+            .id_allocator
+            .lock()
+            .alloc()
+            .ok_or_else(|| Error::with_message(Errno::ENOSPC, "too many IPC objects"))?
+            as key_t;
+        match new_object(id) {
+            Ok(object) => {
+                objects.insert(id, Arc::new(object));
+                Ok(id)
+            }
+            Err(err) => {
+                self.id_allocator.lock().free(id as usize);
+                Err(err)
+            }
+        }
+    }
+
+    /// Returns the object with the ID.
+    pub fn get(&self, id: key_t) -> Result<Arc<T>> {
+        self.objects
+            .read()
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| Error::with_message(Errno::EINVAL, "the IPC ID does not exist"))
+    }
+
+    /// Removes the object with the ID, if it passes the check of `check`.
+    pub fn remove<C>(&self, id: key_t, check: C) -> Result<Arc<T>>
+    where
+        C: FnOnce(&T) -> Result<()>,
+    {
+        let mut objects = self.objects.write();
+
+        let object = objects
+            .get(&id)
+            .ok_or_else(|| Error::with_message(Errno::EINVAL, "the IPC ID does not exist"))?;
+        check(object)?;
+
+        let object = objects.remove(&id).unwrap();
+        self.id_allocator.lock().free(id as usize);
+        Ok(object)
+    }
+
+    /// Removes the objects that do not satisfy the predicate.
+    pub fn retain<F>(&self, mut f: F)
+    where
+        F: FnMut(&T) -> bool,
+    {
+        let mut objects = self.objects.write();
+        let mut id_allocator = self.id_allocator.lock();
+        objects.retain(|id, object| {
+            let is_retained = f(object);
+            if !is_retained {
+                id_allocator.free(*id as usize);
+            }
+            is_retained
+        });
+    }
+
+    /// Locks the objects for reading.
+    pub fn read(&self) -> RwLockReadGuard<BTreeMap<key_t, Arc<T>>, PreemptDisabled> {
+        self.objects.read()
+    }
+}
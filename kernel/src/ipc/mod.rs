@@ -1,15 +1,27 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use aster_rights::ReadOp;
+
+use self::ids::IPC_PRIVATE;
 use crate::{
     prelude::*,
-    process::{Gid, Uid},
+    process::{credentials::capabilities::CapSet, Credentials, Gid, Uid},
 };
 
+pub mod ids;
+pub mod mqueue;
+pub mod msg;
 pub mod semaphore;
+pub mod shm;
 
 #[expect(non_camel_case_types)]
 pub type key_t = i32;
 
+/// The flag in the control commands that requests the 64-bit version of the data structures.
+///
+/// The 64-bit version is the only version supported, so this flag is ignored.
+pub const IPC_64: i32 = 0x100;
+
 bitflags! {
     pub struct IpcFlags: u32{
         /// Create key if key does not exist
@@ -23,6 +35,24 @@ bitflags! {
     }
 }
 
+bitflags! {
+    pub struct PermissionMode: u16{
+        const ALTER  = 0o002;
+        const WRITE  = 0o002;
+        const READ   = 0o004;
+    }
+}
+
+impl PermissionMode {
+    /// Returns the permissions requested by the permission bits in the flags of `*get`.
+    ///
+    /// Like Linux, a permission is requested if it is set for any of the owner, the group, and
+    /// the others.
+    pub fn from_requested_mode(mode: u16) -> Self {
+        Self::from_bits_truncate((mode >> 6) | (mode >> 3) | mode)
+    }
+}
+
 #[repr(i32)]
 #[derive(Debug, Clone, Copy, TryFromInt)]
 #[expect(non_camel_case_types)]
@@ -42,32 +72,45 @@ pub enum IpcControlCmd {
 
 #[derive(Debug)]
 pub struct IpcPermission {
+    /// Creator's UID
+    cuid: Uid,
+    /// Creator's GID
+    cguid: Gid,
+    /// The fields that can be changed after creation
+    inner: SpinLock<IpcPermissionInner>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct IpcPermissionInner {
     key: key_t,
     /// Owner's UID
     uid: Uid,
     /// Owner's GID
     gid: Gid,
-    /// Creator's UID
-    cuid: Uid,
-    /// Creator's GID
-    cguid: Gid,
     /// Permission mode
     mode: u16,
 }
 
 impl IpcPermission {
     pub fn key(&self) -> key_t {
-        self.key
+        self.inner.lock().key
+    }
+
+    /// Makes the IPC object no longer found with its key.
+    ///
+    /// This is used when an IPC object is marked to be removed but is still in use.
+    pub fn set_key_private(&self) {
+        self.inner.lock().key = IPC_PRIVATE;
     }
 
     /// Returns owner's UID
     pub fn uid(&self) -> Uid {
-        self.uid
+        self.inner.lock().uid
     }
 
     /// Returns owner's GID
     pub fn gid(&self) -> Gid {
-        self.gid
+        self.inner.lock().gid
     }
 
     /// Returns creator's UID
@@ -82,21 +125,110 @@ impl IpcPermission {
 
     /// Returns permission mode
     pub fn mode(&self) -> u16 {
-        self.mode
+        self.inner.lock().mode
+    }
+
+    /// Sets the owner and the permission mode, as `IPC_SET` does.
+    ///
+    /// Only the permission bits of `mode` are changed.
+    pub fn set(&self, uid: Uid, gid: Gid, mode: u16) {
+        let mut inner = self.inner.lock();
+        inner.uid = uid;
+        inner.gid = gid;
+        inner.mode = (inner.mode & !0o777) | (mode & 0o777);
+    }
+
+    /// Checks whether the accessor with `credentials` has all the permissions in `required`.
+    ///
+    /// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/ipc/util.c#L528>
+    pub fn check_access(
+        &self,
+        credentials: &Credentials<ReadOp>,
+        required: PermissionMode,
+    ) -> Result<()> {
+        let inner = *self.inner.lock();
+
+        let euid = credentials.euid();
+        let egid = credentials.egid();
+        let granted = if euid == inner.uid || euid == self.cuid {
+            inner.mode >> 6
+        } else if egid == inner.gid
+            || egid == self.cguid
+            || credentials.groups().contains(&inner.gid)
+            || credentials.groups().contains(&self.cguid)
+        {
+            inner.mode >> 3
+        } else {
+            inner.mode
+        };
+
+        if !PermissionMode::from_bits_truncate(granted).contains(required)
+            && !credentials.effective_capset().contains(CapSet::IPC_OWNER)
+        {
+            return_errno_with_message!(Errno::EACCES, "the IPC permission is denied");
+        }
+        Ok(())
     }
 
-    pub(self) fn new_sem_perm(key: key_t, uid: Uid, gid: Gid, mode: u16) -> Self {
+    /// Checks whether the accessor with `credentials` can change or remove the IPC object.
+    ///
+    /// Like Linux, only the owner, the creator, and the privileged users can do so.
+    pub fn check_owner(&self, credentials: &Credentials<ReadOp>) -> Result<()> {
+        let euid = credentials.euid();
+        if euid != self.uid()
+            && euid != self.cuid
+            && !credentials.effective_capset().contains(CapSet::SYS_ADMIN)
+        {
+            return_errno_with_message!(Errno::EPERM, "the IPC object is not owned");
+        }
+        Ok(())
+    }
+
+    /// Returns the permission in the form of `struct ipc64_perm`.
+    pub fn to_c_perm(&self) -> CIpcPerm {
+        let inner = *self.inner.lock();
+        CIpcPerm {
+            key: inner.key,
+            uid: inner.uid.into(),
+            gid: inner.gid.into(),
+            cuid: self.cuid.into(),
+            cgid: self.cguid.into(),
+            mode: inner.mode as u32,
+            ..CIpcPerm::new_zeroed()
+        }
+    }
+
+    pub(self) fn new(key: key_t, uid: Uid, gid: Gid, mode: u16) -> Self {
         Self {
-            key,
-            uid,
-            gid,
             cuid: uid,
             cguid: gid,
-            mode,
+            inner: SpinLock::new(IpcPermissionInner {
+                key,
+                uid,
+                gid,
+                mode: mode & 0o777,
+            }),
         }
     }
 }
 
+/// The permission of an IPC object (`struct ipc64_perm` in Linux).
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+pub struct CIpcPerm {
+    pub key: key_t,
+    pub uid: u32,
+    pub gid: u32,
+    pub cuid: u32,
+    pub cgid: u32,
+    pub mode: u32,
+    pub seq: u16,
+    pub _pad: u16,
+    pub _unused: [u64; 2],
+}
+
 pub(super) fn init() {
     semaphore::init();
+    shm::init();
+    msg::init();
 }
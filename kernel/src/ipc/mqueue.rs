@@ -0,0 +1,433 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! POSIX message queues.
+//!
+//! A message queue holds messages with priorities. The messages of higher priorities are
+//! received first, while the messages of the same priority are received in the order they
+//! are sent. The queues are named and live in the mqueue filesystem (see
+//! [`crate::fs::mqueue`]).
+
+use core::fmt::Write;
+
+use ostd::sync::WaitQueue;
+
+use crate::{
+    events::IoEvents,
+    net::socket::netlink::NetlinkNotifier,
+    prelude::*,
+    process::{
+        signal::{
+            c_types::{sigval_t, SigNotify},
+            sig_num::SigNum,
+            signals::mqueue::MqNotifySignal,
+            PollHandle, Pollee,
+        },
+        Pid, Process, Uid,
+    },
+    time::wait::ManagedTimeout,
+};
+
+/// The number of the message priorities.
+pub const MQ_PRIO_MAX: u32 = 32768;
+
+// The following constant values are derived from the default values in Linux.
+
+/// Default maximum number of messages in a queue.
+pub const DFLT_MSGMAX: usize = 10;
+/// Default maximum size of a message in bytes.
+pub const DFLT_MSGSIZEMAX: usize = 8192;
+/// Maximum number of messages in a queue that unprivileged users can request.
+pub const MSG_MAX: usize = 10;
+/// Maximum size of a message that unprivileged users can request.
+pub const MSGSIZE_MAX: usize = 8192;
+/// Maximum number of messages in a queue.
+pub const HARD_MSGMAX: usize = 65536;
+/// Maximum size of a message in bytes.
+pub const HARD_MSGSIZEMAX: usize = 16 * 1024 * 1024;
+/// Maximum number of queues that unprivileged users can create.
+pub const QUEUES_MAX: usize = 256;
+
+/// The length of the cookie of the notification via a netlink socket.
+pub const NOTIFY_COOKIE_LEN: usize = 32;
+/// The value of the last byte of the cookie, which indicates that a message arrives.
+const NOTIFY_WOKENUP: u8 = 1;
+/// The value of the last byte of the cookie, which indicates that the registration is removed.
+const NOTIFY_REMOVED: u8 = 2;
+
+/// The attributes of a message queue (`struct mq_attr` in Linux).
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+pub struct MqAttr {
+    pub mq_flags: i64,
+    pub mq_maxmsg: i64,
+    pub mq_msgsize: i64,
+    pub mq_curmsgs: i64,
+    pub _reserved: [i64; 4],
+}
+
+/// The way to notify the arrival of a message.
+pub enum MqNotify {
+    /// Nothing is notified, but the registration still takes effect.
+    None,
+    /// A signal is sent to the registered process.
+    ///
+    /// Like Linux, if the signal number is zero, the registration takes effect but no signal
+    /// is sent.
+    Signal(Option<SigNum>, sigval_t),
+    /// The cookie is sent to the netlink socket.
+    ///
+    /// This is how the C library implements `SIGEV_THREAD`. The thread that runs the function
+    /// is spawned by the C library after receiving the cookie.
+    Netlink(NetlinkNotifier, [u8; NOTIFY_COOKIE_LEN]),
+}
+
+impl MqNotify {
+    fn sigev_notify(&self) -> SigNotify {
+        match self {
+            Self::None => SigNotify::SIGEV_NONE,
+            Self::Signal(..) => SigNotify::SIGEV_SIGNAL,
+            Self::Netlink(..) => SigNotify::SIGEV_THREAD,
+        }
+    }
+}
+
+/// The registration of the notification by `mq_notify`.
+struct Registration {
+    process: Weak<Process>,
+    pid: Pid,
+    notify: MqNotify,
+}
+
+impl Registration {
+    /// Notifies the registered process that a message has been sent by the sender.
+    fn notify(self, sender_pid: Pid, sender_uid: Uid) {
+        match self.notify {
+            MqNotify::None => (),
+            MqNotify::Signal(None, _) => (),
+            MqNotify::Signal(Some(sig_num), value) => {
+                if let Some(process) = self.process.upgrade() {
+                    process.enqueue_signal(MqNotifySignal::new(
+                        sig_num, value, sender_pid, sender_uid,
+                    ));
+                }
+            }
+            MqNotify::Netlink(notifier, mut cookie) => {
+                cookie[NOTIFY_COOKIE_LEN - 1] = NOTIFY_WOKENUP;
+                notifier.notify(cookie.to_vec());
+            }
+        }
+    }
+
+    /// Tells the registered process that the registration has been removed.
+    fn remove(self) {
+        if let MqNotify::Netlink(notifier, mut cookie) = self.notify {
+            cookie[NOTIFY_COOKIE_LEN - 1] = NOTIFY_REMOVED;
+            notifier.notify(cookie.to_vec());
+        }
+    }
+}
+
+/// A POSIX message queue.
+pub struct MessageQueue {
+    /// Maximum number of messages
+    maxmsg: usize,
+    /// Maximum size of a message in bytes
+    msgsize: usize,
+    inner: SpinLock<Inner>,
+    /// The senders waiting for the space
+    send_wait_queue: WaitQueue,
+    /// The receivers waiting for the messages
+    recv_wait_queue: WaitQueue,
+    pollee: Pollee,
+}
+
+struct Inner {
+    /// The messages indexed by their priorities
+    messages: BTreeMap<u32, VecDeque<Box<[u8]>>>,
+    /// Current number of messages
+    curmsgs: usize,
+    /// Current number of bytes in the messages
+    qsize: usize,
+    /// Number of the receivers waiting for the messages
+    nr_waiting_receivers: usize,
+    registration: Option<Registration>,
+}
+
+impl MessageQueue {
+    /// Creates a message queue with the attributes.
+    ///
+    /// The attributes should have been checked by [`Self::check_attr`].
+    pub fn new(maxmsg: usize, msgsize: usize) -> Self {
+        Self {
+            maxmsg,
+            msgsize,
+            inner: SpinLock::new(Inner {
+                messages: BTreeMap::new(),
+                curmsgs: 0,
+                qsize: 0,
+                nr_waiting_receivers: 0,
+                registration: None,
+            }),
+            send_wait_queue: WaitQueue::new(),
+            recv_wait_queue: WaitQueue::new(),
+            pollee: Pollee::new(),
+        }
+    }
+
+    /// Creates a message queue with the default attributes.
+    pub fn new_default() -> Self {
+        Self::new(DFLT_MSGMAX, DFLT_MSGSIZEMAX)
+    }
+
+    /// Checks the attributes requested by `mq_open` and returns `(maxmsg, msgsize)`.
+    ///
+    /// Unless `is_privileged` is true, the attributes are limited by [`MSG_MAX`] and
+    /// [`MSGSIZE_MAX`].
+    pub fn check_attr(attr: &MqAttr, is_privileged: bool) -> Result<(usize, usize)> {
+        if attr.mq_maxmsg <= 0 || attr.mq_msgsize <= 0 {
+            return_errno_with_message!(Errno::EINVAL, "the attributes are not positive");
+        }
+
+        let maxmsg = attr.mq_maxmsg as usize;
+        let msgsize = attr.mq_msgsize as usize;
+        let (max_maxmsg, max_msgsize) = if is_privileged {
+            (HARD_MSGMAX, HARD_MSGSIZEMAX)
+        } else {
+            (MSG_MAX, MSGSIZE_MAX)
+        };
+        if maxmsg > max_maxmsg || msgsize > max_msgsize {
+            return_errno_with_message!(Errno::EINVAL, "the attributes exceed the limits");
+        }
+
+        Ok((maxmsg, msgsize))
+    }
+
+    pub fn maxmsg(&self) -> usize {
+        self.maxmsg
+    }
+
+    pub fn msgsize(&self) -> usize {
+        self.msgsize
+    }
+
+    pub fn curmsgs(&self) -> usize {
+        self.inner.lock().curmsgs
+    }
+
+    /// Sends a message with the priority.
+    ///
+    /// If the queue is full, this method waits for the space until the timeout expires, unless
+    /// `is_nonblocking` is true.
+    pub fn send(
+        &self,
+        data: Box<[u8]>,
+        priority: u32,
+        is_nonblocking: bool,
+        timeout: Option<ManagedTimeout>,
+        sender: (Pid, Uid),
+    ) -> Result<()> {
+        debug_assert!(data.len() <= self.msgsize && priority < MQ_PRIO_MAX);
+
+        let mut data = Some(data);
+        let mut try_send = || {
+            let mut inner = self.inner.lock();
+            if inner.curmsgs >= self.maxmsg {
+                if is_nonblocking {
+                    return Some(Err(Error::with_message(
+                        Errno::EAGAIN,
+                        "the message queue is full",
+                    )));
+                }
+                return None;
+            }
+
+            let data = data.take().unwrap();
+            inner.curmsgs += 1;
+            inner.qsize += data.len();
+            inner.messages.entry(priority).or_default().push_back(data);
+
+            // Like Linux, the process is notified only if the queue becomes non-empty and no
+            // receivers are waiting for the message.
+            let registration = if inner.curmsgs == 1 && inner.nr_waiting_receivers == 0 {
+                inner.registration.take()
+            } else {
+                None
+            };
+            Some(Ok(registration))
+        };
+
+        let registration = if let Some(result) = try_send() {
+            result?
+        } else {
+            self.send_wait_queue
+                .pause_until_or_timeout(&mut try_send, timeout)
+                .map_err(map_timeout_error)??
+        };
+
+        self.pollee.notify(IoEvents::IN);
+        self.recv_wait_queue.wake_all();
+
+        if let Some(registration) = registration {
+            registration.notify(sender.0, sender.1);
+        }
+        Ok(())
+    }
+
+    /// Receives the oldest message of the highest priority, and returns the message with its
+    /// priority.
+    ///
+    /// If the queue is empty, this method waits for a message until the timeout expires,
+    /// unless `is_nonblocking` is true.
+    pub fn recv(
+        &self,
+        is_nonblocking: bool,
+        timeout: Option<ManagedTimeout>,
+    ) -> Result<(Box<[u8]>, u32)> {
+        let mut is_waiting = false;
+        let mut try_recv = || {
+            let mut inner = self.inner.lock();
+
+            let Some(mut entry) = inner.messages.last_entry() else {
+                if is_nonblocking {
+                    return Some(Err(Error::with_message(
+                        Errno::EAGAIN,
+                        "the message queue is empty",
+                    )));
+                }
+                if !is_waiting {
+                    inner.nr_waiting_receivers += 1;
+                    is_waiting = true;
+                }
+                return None;
+            };
+
+            let priority = *entry.key();
+            let data = entry.get_mut().pop_front().unwrap();
+            if entry.get().is_empty() {
+                entry.remove();
+            }
+
+            inner.curmsgs -= 1;
+            inner.qsize -= data.len();
+            if is_waiting {
+                inner.nr_waiting_receivers -= 1;
+                is_waiting = false;
+            }
+            Some(Ok((data, priority)))
+        };
+
+        let result = if let Some(result) = try_recv() {
+            result
+        } else {
+            self.recv_wait_queue
+                .pause_until_or_timeout(&mut try_recv, timeout)
+                .map_err(map_timeout_error)
+                .and_then(|result| result)
+        };
+        if is_waiting {
+            self.inner.lock().nr_waiting_receivers -= 1;
+        }
+        let received = result?;
+
+        self.pollee.notify(IoEvents::OUT);
+        self.send_wait_queue.wake_all();
+
+        Ok(received)
+    }
+
+    /// Registers the notification for the process.
+    ///
+    /// Only one process can register the notification at the same time.
+    pub fn register_notify(
+        &self,
+        pid: Pid,
+        process: Weak<Process>,
+        notify: MqNotify,
+    ) -> Result<()> {
+        let mut inner = self.inner.lock();
+        if inner.registration.is_some() {
+            return_errno_with_message!(Errno::EBUSY, "the notification has been registered");
+        }
+
+        inner.registration = Some(Registration {
+            process,
+            pid,
+            notify,
+        });
+        Ok(())
+    }
+
+    /// Removes the notification if it is registered by the process.
+    pub fn unregister_notify(&self, pid: Pid) {
+        let mut inner = self.inner.lock();
+        if inner
+            .registration
+            .as_ref()
+            .is_none_or(|registration| registration.pid != pid)
+        {
+            return;
+        }
+
+        let registration = inner.registration.take().unwrap();
+        drop(inner);
+
+        registration.remove();
+    }
+
+    /// Returns the status of the queue, which is the content of the file in the mqueue
+    /// filesystem.
+    pub fn status(&self) -> String {
+        let inner = self.inner.lock();
+
+        let (notify, signo, notify_pid) = match inner.registration.as_ref() {
+            None => (0, 0, 0),
+            Some(registration) => {
+                let signo = match &registration.notify {
+                    MqNotify::Signal(Some(sig_num), _) => sig_num.as_u8(),
+                    _ => 0,
+                };
+                (
+                    registration.notify.sigev_notify() as i32,
+                    signo,
+                    registration.pid,
+                )
+            }
+        };
+
+        let mut status = String::new();
+        writeln!(
+            status,
+            "QSIZE:{:<10} NOTIFY:{:<5} SIGNO:{:<5} NOTIFY_PID:{:<6}",
+            inner.qsize, notify, signo, notify_pid
+        )
+        .unwrap();
+        status
+    }
+
+    pub fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
+        self.pollee
+            .poll_with(mask, poller, || self.check_io_events())
+    }
+
+    fn check_io_events(&self) -> IoEvents {
+        let inner = self.inner.lock();
+
+        let mut events = IoEvents::empty();
+        if inner.curmsgs > 0 {
+            events |= IoEvents::IN;
+        }
+        if inner.curmsgs < self.maxmsg {
+            events |= IoEvents::OUT;
+        }
+        events
+    }
+}
+
+/// Maps the timeout error of the waiting to the error of the message queue operations.
+fn map_timeout_error(err: Error) -> Error {
+    if err.error() == Errno::ETIME {
+        Error::with_message(Errno::ETIMEDOUT, "the time limit is reached")
+    } else {
+        err
+    }
+}
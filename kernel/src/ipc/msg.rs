@@ -0,0 +1,349 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! System V message queues.
+
+use core::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use aster_rights::ReadOp;
+use ostd::sync::WaitQueue;
+use spin::Once;
+
+use super::{
+    ids::{IpcIds, IpcObject},
+    key_t, IpcPermission,
+};
+use crate::{
+    prelude::*,
+    process::{Credentials, Pid},
+    time::clocks::RealTimeCoarseClock,
+};
+
+// The following constant values are derived from the default values in Linux.
+
+/// Maximum number of message queues.
+pub const MSGMNI: usize = 32000;
+/// Maximum size of a message in bytes.
+pub const MSGMAX: usize = 8192;
+/// Default maximum size of a message queue in bytes.
+pub const MSGMNB: usize = 16384;
+
+bitflags! {
+    /// The flags of `msgsnd` and `msgrcv`.
+    pub struct MsgFlags: u32 {
+        /// Return error instead of waiting.
+        const IPC_NOWAIT  = 0o4000;
+        /// Truncate the message if it is too long.
+        const MSG_NOERROR = 0o10000;
+        /// Receive the first message whose type is not the specified type.
+        const MSG_EXCEPT  = 0o20000;
+        /// Copy the message at the specified position without removing it.
+        const MSG_COPY    = 0o40000;
+    }
+}
+
+/// A message in a message queue.
+#[derive(Debug)]
+pub struct Message {
+    typ: i64,
+    data: Box<[u8]>,
+}
+
+impl Message {
+    pub fn new(typ: i64, data: Box<[u8]>) -> Self {
+        debug_assert!(typ > 0);
+        Self { typ, data }
+    }
+
+    pub fn typ(&self) -> i64 {
+        self.typ
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+/// The way to select a message to receive.
+#[derive(Debug, Clone, Copy)]
+pub enum MsgSelector {
+    /// The first message.
+    First,
+    /// The first message of the type.
+    Type(i64),
+    /// The first message whose type is not the type.
+    ExceptType(i64),
+    /// The first message of the lowest type that is less than or equal to the type.
+    LowestType(i64),
+    /// The message at the position, which is not removed after being received.
+    Copy(usize),
+}
+
+impl MsgSelector {
+    /// Creates a selector from the message type and the flags of `msgrcv`.
+    pub fn new(msgtyp: i64, flags: MsgFlags) -> Result<Self> {
+        if flags.contains(MsgFlags::MSG_COPY) {
+            if flags.contains(MsgFlags::MSG_EXCEPT) || !flags.contains(MsgFlags::IPC_NOWAIT) {
+                return_errno_with_message!(
+                    Errno::EINVAL,
+                    "`MSG_COPY` should be used with only `IPC_NOWAIT`"
+                );
+            }
+            let index = usize::try_from(msgtyp)
+                .map_err(|_| Error::with_message(Errno::EINVAL, "invalid message index"))?;
+            return Ok(Self::Copy(index));
+        }
+
+        let selector = match msgtyp {
+            0 => Self::First,
+            typ if typ > 0 && flags.contains(MsgFlags::MSG_EXCEPT) => Self::ExceptType(typ),
+            typ if typ > 0 => Self::Type(typ),
+            typ => Self::LowestType(typ.checked_neg().unwrap_or(i64::MAX)),
+        };
+        Ok(selector)
+    }
+
+    /// Returns the position of the selected message.
+    fn select(&self, messages: &VecDeque<Message>) -> Option<usize> {
+        match *self {
+            Self::First => (!messages.is_empty()).then_some(0),
+            Self::Type(typ) => messages.iter().position(|message| message.typ == typ),
+            Self::ExceptType(typ) => messages.iter().position(|message| message.typ != typ),
+            Self::LowestType(typ) => messages
+                .iter()
+                .enumerate()
+                .filter(|(_, message)| message.typ <= typ)
+                .min_by_key(|(_, message)| message.typ)
+                .map(|(index, _)| index),
+            Self::Copy(index) => (index < messages.len()).then_some(index),
+        }
+    }
+}
+
+/// A System V message queue.
+pub struct MsgQueue {
+    /// The permission of the queue
+    permission: IpcPermission,
+    /// Whether the queue has been removed
+    is_removed: AtomicBool,
+    inner: SpinLock<MsgQueueInner>,
+    /// The senders waiting for the space
+    send_wait_queue: WaitQueue,
+    /// The receivers waiting for the messages
+    recv_wait_queue: WaitQueue,
+}
+
+#[derive(Debug)]
+struct MsgQueueInner {
+    messages: VecDeque<Message>,
+    /// Current number of bytes in the queue
+    cbytes: usize,
+    /// Maximum number of bytes in the queue
+    qbytes: usize,
+    /// PID of the last `msgsnd`
+    lspid: Pid,
+    /// PID of the last `msgrcv`
+    lrpid: Pid,
+    /// Last `msgsnd` time
+    stime: Duration,
+    /// Last `msgrcv` time
+    rtime: Duration,
+    /// Creation time or last modification via `msgctl`
+    ctime: Duration,
+}
+
+/// The status of a message queue, reported by `IPC_STAT`.
+#[derive(Debug, Clone, Copy)]
+pub struct MsgQueueStat {
+    pub cbytes: usize,
+    pub qnum: usize,
+    pub qbytes: usize,
+    pub lspid: Pid,
+    pub lrpid: Pid,
+    pub stime: Duration,
+    pub rtime: Duration,
+    pub ctime: Duration,
+}
+
+impl MsgQueue {
+    pub fn new(key: key_t, mode: u16, credentials: &Credentials<ReadOp>) -> Self {
+        let permission = IpcPermission::new(key, credentials.euid(), credentials.egid(), mode);
+
+        Self {
+            permission,
+            is_removed: AtomicBool::new(false),
+            inner: SpinLock::new(MsgQueueInner {
+                messages: VecDeque::new(),
+                cbytes: 0,
+                qbytes: MSGMNB,
+                lspid: 0,
+                lrpid: 0,
+                stime: Duration::ZERO,
+                rtime: Duration::ZERO,
+                ctime: RealTimeCoarseClock::get().read_time(),
+            }),
+            send_wait_queue: WaitQueue::new(),
+            recv_wait_queue: WaitQueue::new(),
+        }
+    }
+
+    /// Sends a message to the queue.
+    ///
+    /// If the queue is full, this method waits for the space unless `is_nonblocking` is true.
+    pub fn send(&self, message: Message, is_nonblocking: bool, pid: Pid) -> Result<()> {
+        let mut message = Some(message);
+        let mut try_send = || {
+            if self.is_removed.load(Ordering::Relaxed) {
+                return Some(Err(Error::with_message(
+                    Errno::EIDRM,
+                    "the message queue is removed",
+                )));
+            }
+
+            let mut inner = self.inner.lock();
+            let len = message.as_ref().unwrap().data.len();
+            // Like Linux, the number of messages is also limited by the maximum number of bytes,
+            // so that the zero-length messages cannot consume the memory infinitely.
+            if inner.cbytes + len > inner.qbytes || inner.messages.len() + 1 > inner.qbytes {
+                if is_nonblocking {
+                    return Some(Err(Error::with_message(
+                        Errno::EAGAIN,
+                        "the message queue is full",
+                    )));
+                }
+                return None;
+            }
+
+            inner.messages.push_back(message.take().unwrap());
+            inner.cbytes += len;
+            inner.lspid = pid;
+            inner.stime = RealTimeCoarseClock::get().read_time();
+            Some(Ok(()))
+        };
+
+        if let Some(result) = try_send() {
+            result?;
+        } else {
+            self.send_wait_queue.pause_until(&mut try_send)??;
+        }
+
+        self.recv_wait_queue.wake_all();
+        Ok(())
+    }
+
+    /// Receives a message selected by `selector` from the queue.
+    ///
+    /// If the message is longer than `max_len`, it is truncated if `can_truncate` is true.
+    /// Otherwise, the message is kept in the queue and [`E2BIG`] is returned. If no message is
+    /// selected, this method waits for the message unless `is_nonblocking` is true.
+    ///
+    /// [`E2BIG`]: Errno::E2BIG
+    pub fn recv(
+        &self,
+        selector: MsgSelector,
+        max_len: usize,
+        can_truncate: bool,
+        is_nonblocking: bool,
+        pid: Pid,
+    ) -> Result<(i64, Vec<u8>)> {
+        let try_recv = || {
+            if self.is_removed.load(Ordering::Relaxed) {
+                return Some(Err(Error::with_message(
+                    Errno::EIDRM,
+                    "the message queue is removed",
+                )));
+            }
+
+            let mut inner = self.inner.lock();
+            let Some(index) = selector.select(&inner.messages) else {
+                if is_nonblocking {
+                    return Some(Err(Error::with_message(
+                        Errno::ENOMSG,
+                        "no message of the desired type",
+                    )));
+                }
+                return None;
+            };
+
+            let message = &inner.messages[index];
+            if message.data.len() > max_len && !can_truncate {
+                return Some(Err(Error::with_message(
+                    Errno::E2BIG,
+                    "the message is too long",
+                )));
+            }
+            let len = message.data.len().min(max_len);
+            let received = (message.typ, message.data[..len].to_vec());
+
+            if !matches!(selector, MsgSelector::Copy(_)) {
+                let message = inner.messages.remove(index).unwrap();
+                inner.cbytes -= message.data.len();
+                inner.lrpid = pid;
+                inner.rtime = RealTimeCoarseClock::get().read_time();
+            }
+            Some(Ok(received))
+        };
+
+        let received = if let Some(result) = try_recv() {
+            result?
+        } else {
+            self.recv_wait_queue.pause_until(try_recv)??
+        };
+
+        self.send_wait_queue.wake_all();
+        Ok(received)
+    }
+
+    /// Returns the status of the queue.
+    pub fn stat(&self) -> MsgQueueStat {
+        let inner = self.inner.lock();
+        MsgQueueStat {
+            cbytes: inner.cbytes,
+            qnum: inner.messages.len(),
+            qbytes: inner.qbytes,
+            lspid: inner.lspid,
+            lrpid: inner.lrpid,
+            stime: inner.stime,
+            rtime: inner.rtime,
+            ctime: inner.ctime,
+        }
+    }
+
+    /// Sets the maximum number of bytes in the queue.
+    pub fn set_qbytes(&self, qbytes: usize) {
+        self.inner.lock().qbytes = qbytes;
+        // More messages may be sent if the limit grows.
+        self.send_wait_queue.wake_all();
+    }
+
+    pub fn update_ctime(&self) {
+        self.inner.lock().ctime = RealTimeCoarseClock::get().read_time();
+    }
+
+    /// Marks the queue as removed and wakes up all the waiters.
+    pub fn set_removed(&self) {
+        self.is_removed.store(true, Ordering::Relaxed);
+        self.send_wait_queue.wake_all();
+        self.recv_wait_queue.wake_all();
+    }
+}
+
+impl IpcObject for MsgQueue {
+    fn permission(&self) -> &IpcPermission {
+        &self.permission
+    }
+}
+
+/// Returns the message queues in the system.
+pub fn msg_queues() -> &'static IpcIds<MsgQueue> {
+    MSG_QUEUES.get().unwrap()
+}
+
+/// Message queues in system
+static MSG_QUEUES: Once<IpcIds<MsgQueue>> = Once::new();
+
+pub(super) fn init() {
+    MSG_QUEUES.call_once(|| IpcIds::new(MSGMNI));
+}
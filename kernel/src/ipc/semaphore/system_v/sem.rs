@@ -1,5 +1,6 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use alloc::collections::btree_map::BTreeMap;
 use core::{
     slice::Iter,
     sync::atomic::{AtomicU16, Ordering},
@@ -9,9 +10,9 @@ use core::{
 use atomic_integer_wrapper::define_atomic_version_of_integer_like_type;
 use ostd::sync::{PreemptDisabled, Waiter, Waker};
 
-use super::sem_set::{SemSetInner, SEMVMX};
+use super::sem_set::{SemSetInner, SEMAEM, SEMVMX};
 use crate::{
-    ipc::{key_t, semaphore::system_v::sem_set::sem_sets, IpcFlags},
+    ipc::{key_t, semaphore::system_v::sem_set::sem_sets, IpcFlags, PermissionMode},
     prelude::*,
     process::Pid,
     time::{clocks::JIFFIES_TIMER_MANAGER, timer::Timeout},
@@ -101,6 +102,10 @@ pub struct Semaphore {
     /// - through semctl with SETVAL and SETALL
     /// - through SEM_UNDO when task exit
     latest_modified_pid: Pid,
+    /// The adjustments that will be applied when the processes exit.
+    ///
+    /// The adjustments are recorded by the operations with `SEM_UNDO`.
+    adjustments: BTreeMap<Pid, i32>,
 }
 
 impl Semaphore {
@@ -120,10 +125,44 @@ impl Semaphore {
         self.latest_modified_pid
     }
 
+    /// Returns the adjustment of the process, which will be applied when the process exits.
+    pub fn adjustment(&self, pid: Pid) -> i32 {
+        self.adjustments.get(&pid).copied().unwrap_or(0)
+    }
+
+    /// Clears the adjustments of all the processes.
+    pub(super) fn clear_adjustments(&mut self) {
+        self.adjustments.clear();
+    }
+
+    /// Applies and clears the adjustment of the process.
+    ///
+    /// This method returns whether the value of the semaphore is changed.
+    pub(super) fn undo(&mut self, pid: Pid) -> bool {
+        let Some(adjustment) = self.adjustments.remove(&pid) else {
+            return false;
+        };
+
+        // Like Linux, the value is clamped silently if it gets out of range.
+        self.val = (self.val + adjustment).clamp(0, SEMVMX);
+        self.latest_modified_pid = pid;
+        true
+    }
+
+    fn add_adjustment(&mut self, pid: Pid, delta: i32) {
+        let adjustment = self.adjustment(pid) + delta;
+        if adjustment == 0 {
+            self.adjustments.remove(&pid);
+        } else {
+            self.adjustments.insert(pid, adjustment);
+        }
+    }
+
     pub(super) fn new(val: i32) -> Self {
         Self {
             val,
             latest_modified_pid: current!().pid(),
+            adjustments: BTreeMap::new(),
         }
     }
 }
@@ -145,18 +184,20 @@ pub fn sem_op(
         pid,
     };
 
-    // TODO: Support permission check
-    warn!("Semaphore operation doesn't support permission check now");
-
     let (alter, dupsop) = get_sops_flags(&pending_op);
     if dupsop {
         warn!("Found duplicate sop");
     }
 
-    let local_sem_sets = sem_sets();
-    let sem_set = local_sem_sets
-        .get(&sem_id)
-        .ok_or(Error::new(Errno::EINVAL))?;
+    let sem_set = sem_sets().get(sem_id)?;
+    let required_perm = if alter {
+        PermissionMode::ALTER
+    } else {
+        PermissionMode::READ
+    };
+    sem_set
+        .permission()
+        .check_access(&ctx.posix_thread.credentials(), required_perm)?;
     let mut inner = sem_set.inner();
 
     if perform_atomic_semop(&mut inner.sems, &mut pending_op)? {
@@ -197,16 +238,16 @@ pub fn sem_op(
     }
 
     drop(inner);
-    drop(local_sem_sets);
+    // The semaphore set should not be kept alive while waiting, since the pending operations
+    // are woken up when the removed semaphore set is dropped.
+    drop(sem_set);
 
     waiter.wait();
     match status.load(Ordering::Relaxed) {
         Status::Normal => Ok(()),
         Status::Removed => Err(Error::new(Errno::EIDRM)),
         Status::Pending => {
-            // FIXME: Getting sem_sets maybe time-consuming.
-            let sem_sets = sem_sets();
-            let sem_set = sem_sets.get(&sem_id).ok_or(Error::new(Errno::EINVAL))?;
+            let sem_set = sem_sets().get(sem_id)?;
             let mut inner = sem_set.inner();
 
             let pending_ops = if alter {
@@ -214,7 +255,7 @@ pub fn sem_op(
             } else {
                 &mut inner.pending_const
             };
-            pending_ops.retain(|op| op.pid != pid);
+            pending_ops.retain(|op| !Arc::ptr_eq(&op.status, &status));
 
             Err(Error::new(Errno::EAGAIN))
         }
@@ -345,7 +386,10 @@ fn perform_atomic_semop(sems: &mut Box<[Semaphore]>, pending_op: &mut PendingOp)
             return_errno!(Errno::ERANGE);
         }
         if flags.contains(IpcFlags::SEM_UNDO) {
-            todo!()
+            let adjustment = sem.adjustment(pending_op.pid) - i32::from(op.sem_op);
+            if !(-SEMAEM - 1..=SEMAEM).contains(&adjustment) {
+                return_errno!(Errno::ERANGE);
+            }
         }
     }
 
@@ -355,6 +399,11 @@ fn perform_atomic_semop(sems: &mut Box<[Semaphore]>, pending_op: &mut PendingOp)
         if op.sem_op != 0 {
             sem.val += i32::from(op.sem_op);
             sem.latest_modified_pid = pending_op.pid;
+
+            let flags = IpcFlags::from_bits_truncate(op.sem_flags as u32);
+            if flags.contains(IpcFlags::SEM_UNDO) {
+                sem.add_adjustment(pending_op.pid, -i32::from(op.sem_op));
+            }
         }
     }
 
@@ -1,22 +1,23 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use alloc::{collections::btree_map::BTreeMap, vec::Vec};
+use alloc::vec::Vec;
 use core::{
     sync::atomic::{AtomicU64, Ordering},
     time::Duration,
 };
 
 use aster_rights::ReadOp;
-use id_alloc::IdAlloc;
-use ostd::sync::{PreemptDisabled, RwLockReadGuard, RwLockWriteGuard};
+use ostd::sync::PreemptDisabled;
 use spin::Once;
 
-use super::{
-    sem::{update_pending_alter, wake_const_ops, PendingOp, Status},
-    PermissionMode,
-};
+use super::sem::{update_pending_alter, wake_const_ops, PendingOp, Status};
 use crate::{
-    ipc::{key_t, semaphore::system_v::sem::Semaphore, IpcPermission},
+    ipc::{
+        ids::{IpcIds, IpcObject},
+        key_t,
+        semaphore::system_v::sem::Semaphore,
+        IpcPermission,
+    },
     prelude::*,
     process::{Credentials, Pid},
     time::clocks::RealTimeCoarseClock,
@@ -111,7 +112,7 @@ impl SemaphoreSet {
     }
 
     pub fn setval(&self, sem_num: usize, val: i32, pid: Pid) -> Result<()> {
-        if !(0..SEMVMX).contains(&val) {
+        if !(0..=SEMVMX).contains(&val) {
             return_errno!(Errno::ERANGE);
         }
 
@@ -121,6 +122,7 @@ impl SemaphoreSet {
 
         sem.set_val(val);
         sem.set_latest_modified_pid(pid);
+        sem.clear_adjustments();
 
         let mut wake_queue = LinkedList::new();
         if val == 0 {
@@ -128,18 +130,61 @@ impl SemaphoreSet {
         } else {
             update_pending_alter(sems, pending_alter, pending_const, &mut wake_queue);
         }
+        wake_up_ops(wake_queue);
 
-        for wake_op in wake_queue {
-            wake_op.set_status(Status::Normal);
-            if let Some(waker) = wake_op.waker() {
-                waker.wake_up();
-            }
+        self.update_ctime();
+        Ok(())
+    }
+
+    /// Sets the values of all the semaphores.
+    pub fn setall(&self, vals: &[u16], pid: Pid) -> Result<()> {
+        debug_assert_eq!(vals.len(), self.nsems);
+        if vals.iter().any(|val| i32::from(*val) > SEMVMX) {
+            return_errno!(Errno::ERANGE);
+        }
+
+        let mut inner = self.inner();
+        let (sems, pending_alter, pending_const) = inner.field_mut();
+        for (sem, val) in sems.iter_mut().zip(vals) {
+            sem.set_val(i32::from(*val));
+            sem.set_latest_modified_pid(pid);
+            sem.clear_adjustments();
         }
 
+        let mut wake_queue = LinkedList::new();
+        wake_const_ops(sems, pending_const, &mut wake_queue);
+        update_pending_alter(sems, pending_alter, pending_const, &mut wake_queue);
+        wake_up_ops(wake_queue);
+
         self.update_ctime();
         Ok(())
     }
 
+    /// Returns the values of all the semaphores.
+    pub fn getall(&self) -> Vec<u16> {
+        let inner = self.inner();
+        inner.sems.iter().map(|sem| sem.val() as u16).collect()
+    }
+
+    /// Applies the adjustments of the process when it exits.
+    fn undo(&self, pid: Pid) {
+        let mut inner = self.inner();
+        let (sems, pending_alter, pending_const) = inner.field_mut();
+
+        let mut is_changed = false;
+        for sem in sems.iter_mut() {
+            is_changed |= sem.undo(pid);
+        }
+        if !is_changed {
+            return;
+        }
+
+        let mut wake_queue = LinkedList::new();
+        wake_const_ops(sems, pending_const, &mut wake_queue);
+        update_pending_alter(sems, pending_alter, pending_const, &mut wake_queue);
+        wake_up_ops(wake_queue);
+    }
+
     pub fn get<T>(&self, sem_num: usize, func: &dyn Fn(&Semaphore) -> T) -> Result<T> {
         let inner = self.inner();
         Ok(func(
@@ -162,6 +207,10 @@ impl SemaphoreSet {
         );
     }
 
+    pub fn sem_otime(&self) -> Duration {
+        Duration::from_secs(self.sem_otime.load(Ordering::Relaxed))
+    }
+
     pub fn update_otime(&self) {
         self.sem_otime.store(
             RealTimeCoarseClock::get().read_time().as_secs(),
@@ -173,7 +222,12 @@ impl SemaphoreSet {
         self.inner.lock()
     }
 
-    fn new(key: key_t, nsems: usize, mode: u16, credentials: Credentials<ReadOp>) -> Result<Self> {
+    pub fn new(
+        key: key_t,
+        nsems: usize,
+        mode: u16,
+        credentials: &Credentials<ReadOp>,
+    ) -> Result<Self> {
         debug_assert!(nsems <= SEMMSL);
 
         let mut sems = Vec::with_capacity(nsems);
@@ -181,8 +235,7 @@ impl SemaphoreSet {
             sems.push(Semaphore::new(0));
         }
 
-        let permission =
-            IpcPermission::new_sem_perm(key, credentials.euid(), credentials.egid(), mode);
+        let permission = IpcPermission::new(key, credentials.euid(), credentials.egid(), mode);
 
         Ok(Self {
             nsems,
@@ -218,97 +271,42 @@ impl Drop for SemaphoreSet {
             }
         }
         pending_const.clear();
-
-        ID_ALLOCATOR
-            .get()
-            .unwrap()
-            .lock()
-            .free(self.permission.key() as usize);
     }
 }
 
-pub fn create_sem_set_with_id(
-    id: key_t,
-    nsems: usize,
-    mode: u16,
-    credentials: Credentials<ReadOp>,
-) -> Result<()> {
-    debug_assert!(nsems <= SEMMSL);
-    debug_assert!(id > 0);
-    if id as usize > SEMMNI {
-        return_errno_with_message!(Errno::ENOENT, "id larger than SEMMNI");
+impl IpcObject for SemaphoreSet {
+    fn permission(&self) -> &IpcPermission {
+        &self.permission
     }
-
-    ID_ALLOCATOR
-        .get()
-        .unwrap()
-        .lock()
-        .alloc_specific(id as usize)
-        .ok_or(Error::new(Errno::EEXIST))?;
-
-    let mut sem_sets = SEMAPHORE_SETS.write();
-    sem_sets.insert(id, SemaphoreSet::new(id, nsems, mode, credentials)?);
-
-    Ok(())
 }
 
-/// Checks the semaphore. Return Ok if the semaphore exists and pass the check.
-pub fn check_sem(id: key_t, nsems: Option<usize>, required_perm: PermissionMode) -> Result<()> {
-    debug_assert!(id > 0);
-
-    let sem_sets = SEMAPHORE_SETS.read();
-    let sem_set = sem_sets.get(&id).ok_or(Error::new(Errno::ENOENT))?;
-
-    if let Some(nsems) = nsems {
-        debug_assert!(nsems <= SEMMSL);
-        if nsems > sem_set.nsems() {
-            return_errno!(Errno::EINVAL);
+fn wake_up_ops(wake_queue: LinkedList<PendingOp>) {
+    for wake_op in wake_queue {
+        wake_op.set_status(Status::Normal);
+        if let Some(waker) = wake_op.waker() {
+            waker.wake_up();
         }
     }
-
-    if !required_perm.is_empty() {
-        // TODO: Support permission check
-        warn!("Semaphore doesn't support permission check now");
-    }
-
-    Ok(())
-}
-
-pub fn create_sem_set(nsems: usize, mode: u16, credentials: Credentials<ReadOp>) -> Result<key_t> {
-    debug_assert!(nsems <= SEMMSL);
-
-    let id = ID_ALLOCATOR
-        .get()
-        .unwrap()
-        .lock()
-        .alloc()
-        .ok_or(Error::new(Errno::ENOSPC))? as i32;
-
-    let mut sem_sets = SEMAPHORE_SETS.write();
-    sem_sets.insert(id, SemaphoreSet::new(id, nsems, mode, credentials)?);
-
-    Ok(id)
 }
 
-pub fn sem_sets<'a>() -> RwLockReadGuard<'a, BTreeMap<key_t, SemaphoreSet>, PreemptDisabled> {
-    SEMAPHORE_SETS.read()
+/// Applies the semaphore adjustments of the process when it exits.
+///
+/// The adjustments are recorded by the operations with `SEM_UNDO`.
+pub fn undo_sem_adjustments(pid: Pid) {
+    let sem_sets: Vec<_> = sem_sets().read().values().cloned().collect();
+    for sem_set in sem_sets {
+        sem_set.undo(pid);
+    }
 }
 
-pub fn sem_sets_mut<'a>() -> RwLockWriteGuard<'a, BTreeMap<key_t, SemaphoreSet>, PreemptDisabled> {
-    SEMAPHORE_SETS.write()
+/// Returns the semaphore sets in the system.
+pub fn sem_sets() -> &'static IpcIds<SemaphoreSet> {
+    SEMAPHORE_SETS.get().unwrap()
 }
 
-static ID_ALLOCATOR: Once<SpinLock<IdAlloc>> = Once::new();
-
 /// Semaphore sets in system
-static SEMAPHORE_SETS: RwLock<BTreeMap<key_t, SemaphoreSet>> = RwLock::new(BTreeMap::new());
+static SEMAPHORE_SETS: Once<IpcIds<SemaphoreSet>> = Once::new();
 
 pub(super) fn init() {
-    ID_ALLOCATOR.call_once(|| {
-        let mut id_alloc = IdAlloc::with_capacity(SEMMNI + 1);
-        // Remove the first index 0
-        id_alloc.alloc();
-
-        SpinLock::new(id_alloc)
-    });
+    SEMAPHORE_SETS.call_once(|| IpcIds::new(SEMMNI));
 }
@@ -0,0 +1,206 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! System V shared memory.
+
+use core::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use align_ext::AlignExt;
+use aster_rights::{ReadOp, Rights};
+use spin::Once;
+
+use super::{
+    ids::{IpcIds, IpcObject},
+    key_t, IpcPermission,
+};
+use crate::{
+    prelude::*,
+    process::{Credentials, Pid},
+    time::clocks::RealTimeCoarseClock,
+    vm::vmo::{Vmo, VmoOptions},
+};
+
+// The following constant values are derived from the default values in Linux.
+
+/// Minimum size of a shared memory segment.
+pub const SHMMIN: usize = 1;
+/// Maximum number of shared memory segments.
+pub const SHMMNI: usize = 4096;
+/// Maximum size of a shared memory segment.
+pub const SHMMAX: usize = usize::MAX - (1 << 24);
+/// The alignment of the attaching addresses.
+pub const SHMLBA: usize = PAGE_SIZE;
+
+/// The mode flag that indicates the segment will be destroyed after the last detach.
+const SHM_DEST: u32 = 0o1000;
+
+bitflags! {
+    /// The flags of `shmat`.
+    pub struct ShmFlags: u32 {
+        /// Attach the segment for read-only access.
+        const SHM_RDONLY = 0o10000;
+        /// Round the attaching address down to the multiple of `SHMLBA`.
+        const SHM_RND    = 0o20000;
+        /// Replace the existing mappings at the attaching address.
+        const SHM_REMAP  = 0o40000;
+        /// Allow the contents of the segment to be executed.
+        const SHM_EXEC   = 0o100000;
+    }
+}
+
+/// A System V shared memory segment.
+///
+/// The memory of the segment is a VMO, which is mapped into the processes that attach the
+/// segment. So the segment is attached as many times as the mappings of the VMO, including the
+/// mappings inherited by forking.
+#[derive(Debug)]
+pub struct ShmSegment {
+    /// The size of the segment in bytes
+    size: usize,
+    /// The memory of the segment
+    vmo: Vmo<Rights>,
+    /// The permission of the segment
+    permission: IpcPermission,
+    /// PID of the creator
+    creator_pid: Pid,
+    /// Whether the segment will be destroyed after the last detach
+    is_destroyed: AtomicBool,
+    inner: SpinLock<ShmSegmentInner>,
+}
+
+#[derive(Debug)]
+struct ShmSegmentInner {
+    /// PID of the last `shmat` or `shmdt`
+    latest_pid: Pid,
+    /// Last attach time
+    atime: Duration,
+    /// Last detach time
+    dtime: Duration,
+    /// Creation time or last modification via `shmctl`
+    ctime: Duration,
+}
+
+impl ShmSegment {
+    pub fn new(
+        key: key_t,
+        size: usize,
+        mode: u16,
+        credentials: &Credentials<ReadOp>,
+        pid: Pid,
+    ) -> Result<Self> {
+        let vmo = VmoOptions::<Rights>::new(size.align_up(PAGE_SIZE)).alloc()?;
+        let permission = IpcPermission::new(key, credentials.euid(), credentials.egid(), mode);
+
+        Ok(Self {
+            size,
+            vmo,
+            permission,
+            creator_pid: pid,
+            is_destroyed: AtomicBool::new(false),
+            inner: SpinLock::new(ShmSegmentInner {
+                latest_pid: 0,
+                atime: Duration::ZERO,
+                dtime: Duration::ZERO,
+                ctime: RealTimeCoarseClock::get().read_time(),
+            }),
+        })
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn vmo(&self) -> &Vmo<Rights> {
+        &self.vmo
+    }
+
+    pub fn creator_pid(&self) -> Pid {
+        self.creator_pid
+    }
+
+    /// Returns the number of the current attaches.
+    pub fn nattch(&self) -> usize {
+        // The segment itself holds one capability of the VMO.
+        self.vmo.capability_count() - 1
+    }
+
+    /// Returns the mode in the form of `struct ipc64_perm`, with the flags of the segment.
+    pub fn c_mode(&self) -> u32 {
+        let mode = self.permission.mode() as u32;
+        if self.is_destroyed.load(Ordering::Relaxed) {
+            mode | SHM_DEST
+        } else {
+            mode
+        }
+    }
+
+    /// Marks the segment to be destroyed after the last detach.
+    pub fn destroy(&self) {
+        self.permission.set_key_private();
+        self.is_destroyed.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns the PID of the last `shmat` or `shmdt`.
+    pub fn latest_pid(&self) -> Pid {
+        self.inner.lock().latest_pid
+    }
+
+    pub fn atime(&self) -> Duration {
+        self.inner.lock().atime
+    }
+
+    pub fn dtime(&self) -> Duration {
+        self.inner.lock().dtime
+    }
+
+    pub fn ctime(&self) -> Duration {
+        self.inner.lock().ctime
+    }
+
+    /// Records that the segment has been attached by the process.
+    pub fn on_attach(&self, pid: Pid) {
+        let mut inner = self.inner.lock();
+        inner.latest_pid = pid;
+        inner.atime = RealTimeCoarseClock::get().read_time();
+    }
+
+    /// Records that the segment has been detached by the process.
+    pub fn on_detach(&self, pid: Pid) {
+        let mut inner = self.inner.lock();
+        inner.latest_pid = pid;
+        inner.dtime = RealTimeCoarseClock::get().read_time();
+    }
+
+    pub fn update_ctime(&self) {
+        self.inner.lock().ctime = RealTimeCoarseClock::get().read_time();
+    }
+}
+
+impl IpcObject for ShmSegment {
+    fn permission(&self) -> &IpcPermission {
+        &self.permission
+    }
+}
+
+/// Removes the segments that have been marked to be destroyed and are no longer attached.
+///
+/// Since the segments can be detached implicitly (e.g., by `munmap` or when the process exits),
+/// this should be called before looking up the segments.
+pub fn remove_detached_segments() {
+    shm_segments()
+        .retain(|segment| !segment.is_destroyed.load(Ordering::Relaxed) || segment.nattch() > 0);
+}
+
+/// Returns the shared memory segments in the system.
+pub fn shm_segments() -> &'static IpcIds<ShmSegment> {
+    SHM_SEGMENTS.get().unwrap()
+}
+
+/// Shared memory segments in system
+static SHM_SEGMENTS: Once<IpcIds<ShmSegment>> = Once::new();
+
+pub(super) fn init() {
+    SHM_SEGMENTS.call_once(|| IpcIds::new(SHMMNI));
+}
@@ -39,6 +39,10 @@ pub enum ModuleArg {
 pub struct KCmdlineArg {
     initproc: InitprocArgs,
     module_args: BTreeMap<String, Vec<ModuleArg>>,
+    ip_config: Option<String>,
+    root: Option<String>,
+    nfs_root: Option<String>,
+    hugepages: Vec<(usize, usize)>,
 }
 
 // Define get APIs.
@@ -59,6 +63,37 @@ impl KCmdlineArg {
     pub fn get_module_args(&self, module: &str) -> Option<&Vec<ModuleArg>> {
         self.module_args.get(module)
     }
+    /// Gets the IP configuration of the boot network devices (the `ip=` option).
+    pub fn get_ip_config(&self) -> Option<&str> {
+        self.ip_config.as_deref()
+    }
+    /// Gets the device of the root file system (the `root=` option).
+    pub fn get_root(&self) -> Option<&str> {
+        self.root.as_deref()
+    }
+    /// Gets the NFS root file system (the `nfsroot=` option).
+    pub fn get_nfs_root(&self) -> Option<&str> {
+        self.nfs_root.as_deref()
+    }
+    /// Gets the sizes (in bytes) and the numbers of the huge pages to reserve (the
+    /// `hugepagesz=` and `hugepages=` options).
+    pub fn get_hugepages(&self) -> &[(usize, usize)] {
+        &self.hugepages
+    }
+}
+
+/// The size of the huge pages before any `hugepagesz=` option, which is 2 MiB.
+const DEFAULT_HUGEPAGE_SIZE: usize = 2 << 20;
+
+// Parses a size with an optional suffix of `K`, `M` or `G`, e.g., `2M`.
+fn parse_size(value: &str) -> Option<usize> {
+    let (digits, shift) = match value.as_bytes().last()?.to_ascii_uppercase() {
+        b'K' => (&value[..value.len() - 1], 10),
+        b'M' => (&value[..value.len() - 1], 20),
+        b'G' => (&value[..value.len() - 1], 30),
+        _ => (value, 0),
+    };
+    digits.parse::<usize>().ok()?.checked_mul(1 << shift)
 }
 
 // Splits the command line string by spaces but preserve
@@ -86,7 +121,13 @@ impl From<&str> for KCmdlineArg {
                 envp: Vec::new(),
             },
             module_args: BTreeMap::new(),
+            ip_config: None,
+            root: None,
+            nfs_root: None,
+            hugepages: Vec::new(),
         };
+        // The `hugepages=` option applies to the size of the last `hugepagesz=` option.
+        let mut hugepage_size = DEFAULT_HUGEPAGE_SIZE;
 
         // Every thing after the "--" mark is the initproc arguments.
         let mut kcmdline_end = false;
@@ -109,17 +150,10 @@ impl From<&str> for KCmdlineArg {
                 continue;
             }
             // Arg => Entry | Entry "=" Value
-            let arg_pattern: Vec<_> = arg.split('=').collect();
-            let (entry, value) = match arg_pattern.len() {
-                1 => (arg_pattern[0], None),
-                2 => (arg_pattern[0], Some(arg_pattern[1])),
-                _ => {
-                    log::warn!(
-                        "[KCmdline] Unable to parse kernel argument {}, skip for now",
-                        arg
-                    );
-                    continue;
-                }
+            // The value may contain '=' as well, e.g., `nfsroot=/srv/nfs,vers=3`.
+            let (entry, value) = match arg.split_once('=') {
+                Some((entry, value)) => (entry, Some(value)),
+                None => (arg, None),
             };
             // Entry => Module "." ModuleOptionName | KernelOptionName
             let entry_pattern: Vec<_> = entry.split('.').collect();
@@ -161,6 +195,23 @@ impl From<&str> for KCmdlineArg {
                         }
                         result.initproc.path = Some(value.to_string());
                     }
+                    "ip" => {
+                        result.ip_config = Some(value.to_string());
+                    }
+                    "root" => {
+                        result.root = Some(value.to_string());
+                    }
+                    "nfsroot" => {
+                        result.nfs_root = Some(value.to_string());
+                    }
+                    "hugepagesz" => match parse_size(value) {
+                        Some(size) => hugepage_size = size,
+                        None => log::warn!("[KCmdline] Invalid huge page size {}", value),
+                    },
+                    "hugepages" => match value.parse() {
+                        Ok(nr_pages) => result.hugepages.push((hugepage_size, nr_pages)),
+                        Err(_) => log::warn!("[KCmdline] Invalid huge page number {}", value),
+                    },
                     _ => {
                         // If the option is not recognized, it is passed to the initproc.
                         // Pattern 'option=value' is treated as the init environment.
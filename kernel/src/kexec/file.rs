@@ -0,0 +1,317 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Loading images from files, as done by `kexec_file_load`.
+//!
+//! The kernel image can be an ELF file, whose loadable segments are loaded at their physical
+//! addresses, or a RISC-V `Image` file, which is loaded at its text offset from the start of
+//! the RAM (or the crash kernel region for crash images). The ELF core header (for crash
+//! images), the initramfs, and the device tree are placed at the top of the memory (or the
+//! crash kernel region), like what the firmware usually does.
+
+use alloc::borrow::Cow;
+use core::ops::Range;
+
+use align_ext::AlignExt;
+use ostd::{
+    arch::{
+        boot::DEVICE_TREE,
+        kexec::{
+            crash::{self, CRASH_KERNEL_ALIGN},
+            fdt::build_device_tree,
+        },
+    },
+    boot::{boot_info, memory_region::MemoryRegionType},
+    mm::Paddr,
+};
+use xmas_elf::{header, program, ElfFile};
+
+use super::{lock, new_image, set_image};
+use crate::{prelude::*, util::random::getrandom};
+
+/// The header of RISC-V `Image` files.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct ImageHeader {
+    code0: u32,
+    code1: u32,
+    text_offset: u64,
+    image_size: u64,
+    flags: u64,
+    version: u32,
+    res1: u32,
+    res2: u64,
+    /// "RISCV\0\0\0", which is deprecated but still set
+    magic: u64,
+    /// "RSC\x05"
+    magic2: u32,
+    res3: u32,
+}
+
+const IMAGE_MAGIC: u64 = u64::from_le_bytes(*b"RISCV\0\0\0");
+const IMAGE_MAGIC2: u32 = u32::from_le_bytes(*b"RSC\x05");
+
+/// The length of the seed of the random number generator passed to the new kernel.
+const RNG_SEED_LEN: usize = 64;
+
+/// A segment of the image and its destination.
+struct FileSegment<'a> {
+    data: Cow<'a, [u8]>,
+    mem: Range<Paddr>,
+}
+
+/// Loads an image with the kernel image, the initramfs, and the kernel command line.
+pub fn load_file_image(
+    kernel: &[u8],
+    initrd: Option<&[u8]>,
+    cmdline: &str,
+    is_crash: bool,
+) -> Result<()> {
+    let _guard = lock()?;
+
+    let crash_region = if is_crash {
+        let Some(region) = crash::crash_kernel_region() else {
+            return_errno_with_message!(Errno::EADDRNOTAVAIL, "no crash kernel region is reserved");
+        };
+        Some(region)
+    } else {
+        None
+    };
+
+    let (entry, kernel_segment) = parse_kernel(kernel, crash_region.as_ref())?;
+    let window = match crash_region.clone() {
+        Some(region) => region,
+        None => highest_usable_region()?,
+    };
+    let mut placer = Placer {
+        window: window.clone(),
+        top: window.end,
+        kernel: kernel_segment.mem.clone(),
+    };
+    let mut segments = vec![kernel_segment];
+
+    let elf_core_header = if is_crash {
+        let header = crash::build_elf_core_header();
+        let mem = placer.place(header.len())?;
+        let range = mem.start..mem.start + header.len();
+        segments.push(FileSegment {
+            data: Cow::Owned(header),
+            mem,
+        });
+        Some(range)
+    } else {
+        None
+    };
+
+    let initrd_range = if let Some(initrd) = initrd {
+        let mem = placer.place(initrd.len())?;
+        let range = mem.start..mem.start + initrd.len();
+        segments.push(FileSegment {
+            data: Cow::Borrowed(initrd),
+            mem,
+        });
+        Some(range)
+    } else {
+        None
+    };
+
+    let bootargs = [cmdline.as_bytes(), b"\0"].concat();
+    let initrd_start = initrd_range
+        .as_ref()
+        .map(|range| (range.start as u64).to_be_bytes());
+    let initrd_end = initrd_range
+        .as_ref()
+        .map(|range| (range.end as u64).to_be_bytes());
+    let elf_core_header = elf_core_header.map(reg_property);
+    let usable_memory_range = crash_region.map(reg_property);
+    // The seed in the current device tree has been used, so a new one is generated.
+    let mut rng_seed = [0u8; RNG_SEED_LEN];
+    getrandom(&mut rng_seed)?;
+
+    let device_tree = build_device_tree(&[
+        ("bootargs", (!cmdline.is_empty()).then_some(&bootargs[..])),
+        (
+            "linux,initrd-start",
+            initrd_start.as_ref().map(|val| &val[..]),
+        ),
+        ("linux,initrd-end", initrd_end.as_ref().map(|val| &val[..])),
+        (
+            "linux,elfcorehdr",
+            elf_core_header.as_ref().map(|val| &val[..]),
+        ),
+        (
+            "linux,usable-memory-range",
+            usable_memory_range.as_ref().map(|val| &val[..]),
+        ),
+        ("rng-seed", Some(&rng_seed[..])),
+        // The memory map passed by the EFI stub may have been overwritten, so the new kernel
+        // should use the memory nodes instead.
+        ("linux,uefi-mmap-start", None),
+        ("linux,uefi-mmap-size", None),
+        ("linux,uefi-mmap-desc-size", None),
+        ("linux,uefi-mmap-desc-ver", None),
+    ]);
+    let mem = placer.place(device_tree.len())?;
+    segments.push(FileSegment {
+        data: Cow::Owned(device_tree),
+        mem,
+    });
+
+    let destinations: Vec<_> = segments.iter().map(|segment| segment.mem.clone()).collect();
+    let mut image = new_image(entry, &destinations, is_crash)?;
+    for (index, segment) in segments.iter().enumerate() {
+        let mut reader = VmReader::from(&segment.data[..]).to_fallible();
+        image.load_segment(index, &mut reader)?;
+    }
+    set_image(image)
+}
+
+/// Parses the kernel image and returns the entry and the segment.
+fn parse_kernel<'a>(
+    kernel: &'a [u8],
+    crash_region: Option<&Range<Paddr>>,
+) -> Result<(Paddr, FileSegment<'a>)> {
+    if kernel.starts_with(b"\x7fELF") {
+        return parse_elf(kernel);
+    }
+
+    let Some(header) = kernel
+        .get(..size_of::<ImageHeader>())
+        .map(ImageHeader::from_bytes)
+    else {
+        return_errno_with_message!(Errno::ENOEXEC, "the kernel image is too short");
+    };
+    if header.magic2 != IMAGE_MAGIC2 && header.magic != IMAGE_MAGIC {
+        return_errno_with_message!(Errno::ENOEXEC, "the kernel image format is not supported");
+    }
+
+    let base = match crash_region {
+        Some(region) => region.start,
+        None => DEVICE_TREE
+            .get()
+            .unwrap()
+            .memory()
+            .regions()
+            .map(|region| region.starting_address as usize)
+            .min()
+            .unwrap()
+            .align_up(CRASH_KERNEL_ALIGN),
+    };
+    let start = base
+        .checked_add(header.text_offset as usize)
+        .filter(|start| start % PAGE_SIZE == 0)
+        .ok_or_else(|| Error::with_message(Errno::ENOEXEC, "the text offset is invalid"))?;
+    let size = (header.image_size as usize)
+        .max(kernel.len())
+        .align_up(PAGE_SIZE);
+
+    let segment = FileSegment {
+        data: Cow::Borrowed(kernel),
+        mem: start..start + size,
+    };
+    Ok((start, segment))
+}
+
+/// Parses the ELF kernel image, whose loadable segments are merged into one segment.
+fn parse_elf(kernel: &[u8]) -> Result<(Paddr, FileSegment)> {
+    let elf = ElfFile::new(kernel)
+        .map_err(|_| Error::with_message(Errno::ENOEXEC, "the ELF file is invalid"))?;
+    if elf.header.pt1.class() != header::Class::SixtyFour
+        || elf.header.pt2.machine().as_machine() != header::Machine::RISC_V
+    {
+        return_errno_with_message!(Errno::ENOEXEC, "the ELF file is not for RISC-V 64");
+    }
+
+    let loads: Vec<_> = elf
+        .program_iter()
+        .filter(|ph| ph.get_type() == Ok(program::Type::Load) && ph.mem_size() > 0)
+        .collect();
+    let (Some(start), Some(end)) = (
+        loads.iter().map(|ph| ph.physical_addr() as usize).min(),
+        loads
+            .iter()
+            .map(|ph| ph.physical_addr().saturating_add(ph.mem_size()) as usize)
+            .max(),
+    ) else {
+        return_errno_with_message!(Errno::ENOEXEC, "the ELF file has no loadable segments");
+    };
+    let start = start.align_down(PAGE_SIZE);
+    let end = end.align_up(PAGE_SIZE);
+
+    let mut data = Vec::new();
+    for ph in loads.iter() {
+        let offset = ph.offset() as usize;
+        let src = offset
+            .checked_add(ph.file_size() as usize)
+            .and_then(|end| kernel.get(offset..end))
+            .ok_or_else(|| Error::with_message(Errno::ENOEXEC, "the segment is truncated"))?;
+        let dst = ph.physical_addr() as usize - start;
+        if data.len() < dst + src.len() {
+            data.resize(dst + src.len(), 0);
+        }
+        data[dst..dst + src.len()].copy_from_slice(src);
+    }
+
+    // The entry point is a virtual address, which is translated with the segments.
+    let entry = elf.header.pt2.entry_point();
+    let Some(entry) = loads
+        .iter()
+        .find(|ph| (ph.virtual_addr()..ph.virtual_addr() + ph.mem_size()).contains(&entry))
+        .map(|ph| (entry - ph.virtual_addr() + ph.physical_addr()) as usize)
+    else {
+        return_errno_with_message!(Errno::ENOEXEC, "the entry point is not in the segments");
+    };
+
+    let segment = FileSegment {
+        data: Cow::Owned(data),
+        mem: start..end,
+    };
+    Ok((entry, segment))
+}
+
+/// Returns the usable memory region with the highest address.
+fn highest_usable_region() -> Result<Range<Paddr>> {
+    boot_info()
+        .memory_regions
+        .iter()
+        .filter(|region| region.typ() == MemoryRegionType::Usable)
+        .map(|region| region.base()..region.end())
+        .max_by_key(|region| region.end)
+        .ok_or_else(|| Error::with_message(Errno::ENOMEM, "no usable memory"))
+}
+
+/// Places the segments from the top of the window downwards.
+struct Placer {
+    window: Range<Paddr>,
+    top: Paddr,
+    /// The destination of the kernel image, which must not be overlapped
+    kernel: Range<Paddr>,
+}
+
+impl Placer {
+    fn place(&mut self, len: usize) -> Result<Range<Paddr>> {
+        let len = len.align_up(PAGE_SIZE);
+        let Some(start) = self
+            .top
+            .checked_sub(len)
+            .map(|start| start.align_down(PAGE_SIZE))
+            .filter(|start| *start >= self.window.start)
+        else {
+            return_errno_with_message!(Errno::ENOMEM, "no space for the segments");
+        };
+
+        let range = start..start + len;
+        if range.start < self.kernel.end && self.kernel.start < range.end {
+            return_errno_with_message!(Errno::ENOMEM, "no space for the segments");
+        }
+        self.top = start;
+        Ok(range)
+    }
+}
+
+/// Encodes the range as a `reg`-like property with two cells for both the address and size.
+fn reg_property(range: Range<Paddr>) -> [u8; 16] {
+    let mut property = [0u8; 16];
+    property[..8].copy_from_slice(&(range.start as u64).to_be_bytes());
+    property[8..].copy_from_slice(&(range.len() as u64).to_be_bytes());
+    property
+}
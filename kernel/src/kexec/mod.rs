@@ -0,0 +1,103 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Kexec and crash dumps on RISC-V.
+//!
+//! The image of a new kernel is loaded by `kexec_load` (where the user space places the
+//! segments) or `kexec_file_load` (where the kernel parses the files and places the segments,
+//! see [`load_file_image`]). The normal image is executed by `reboot(LINUX_REBOOT_CMD_KEXEC)`,
+//! and the crash image is executed when the kernel panics. Staging and executing the images
+//! are done by OSTD (see [`ostd::arch::kexec`]).
+//!
+//! If the kernel is booted by a crash image, the memory of the crashed kernel is exported as
+//! `/proc/vmcore` (see [`vmcore`]).
+
+use core::ops::Range;
+
+use ostd::{
+    arch::kexec::{self as arch_kexec, KexecImage},
+    mm::Paddr,
+};
+
+use crate::prelude::*;
+
+mod file;
+pub mod vmcore;
+
+pub use file::load_file_image;
+
+/// The lock that serializes the loading of the images.
+///
+/// Like Linux, the loading fails with [`EBUSY`] instead of waiting if another loading is in
+/// progress.
+///
+/// [`EBUSY`]: Errno::EBUSY
+static KEXEC_LOCK: Mutex<()> = Mutex::new(());
+
+/// Loads an image with the segments in the user space, as done by `kexec_load`.
+///
+/// Each segment is a pair of the user buffer and the destination, and the buffer must not be
+/// longer than the destination.
+pub fn load_image(
+    entry: Paddr,
+    segments: &[(Range<Vaddr>, Range<Paddr>)],
+    is_crash: bool,
+    ctx: &Context,
+) -> Result<()> {
+    let _guard = lock()?;
+
+    let destinations: Vec<_> = segments.iter().map(|(_, mem)| mem.clone()).collect();
+    let mut image = new_image(entry, &destinations, is_crash)?;
+    for (index, (buf, _)) in segments.iter().enumerate() {
+        let mut reader = ctx.user_space().reader(buf.start, buf.len())?;
+        image.load_segment(index, &mut reader)?;
+    }
+    set_image(image)
+}
+
+/// Unloads the normal image or the crash image.
+pub fn unload_image(is_crash: bool) -> Result<()> {
+    let _guard = lock()?;
+    arch_kexec::unload_image(is_crash);
+    Ok(())
+}
+
+/// Executes the normal image.
+///
+/// This returns only if no image is loaded.
+pub fn execute_image() -> Result<()> {
+    let Some(image) = arch_kexec::take_image() else {
+        return_errno_with_message!(Errno::EINVAL, "no kexec image is loaded");
+    };
+
+    // TODO: Shut down the devices, so that they do not access the memory of the new kernel.
+    println!("Starting new kernel");
+    image.execute()
+}
+
+fn lock() -> Result<MutexGuard<'static, ()>> {
+    KEXEC_LOCK
+        .try_lock()
+        .ok_or_else(|| Error::with_message(Errno::EBUSY, "another image is being loaded"))
+}
+
+fn new_image(entry: Paddr, segments: &[Range<Paddr>], is_crash: bool) -> Result<KexecImage> {
+    KexecImage::new(entry, segments, is_crash).map_err(|err| match err {
+        ostd::Error::AccessDenied if is_crash => Error::with_message(
+            Errno::EADDRNOTAVAIL,
+            "the segment is not in the crash kernel region",
+        ),
+        ostd::Error::AccessDenied => {
+            Error::with_message(Errno::EADDRNOTAVAIL, "the segment is not in the RAM")
+        }
+        err => Error::from(err),
+    })
+}
+
+fn set_image(image: KexecImage) -> Result<()> {
+    arch_kexec::set_image(image).map_err(|err| match err {
+        ostd::Error::InvalidArgs => {
+            Error::with_message(Errno::EINVAL, "no segment contains the device tree")
+        }
+        err => Error::from(err),
+    })
+}
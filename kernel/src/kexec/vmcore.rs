@@ -0,0 +1,140 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The memory of the crashed kernel, which is exported as `/proc/vmcore`.
+//!
+//! The file is an ELF core file. Its program headers are copied from the ELF core header
+//! passed by the crashed kernel (see [`crash::elf_core_header`]), except that the file
+//! offsets of the segments, which are the physical addresses in the passed header, are
+//! changed to the offsets of the data in the file. The data of the segments is read from the
+//! memory of the crashed kernel on demand.
+
+use align_ext::AlignExt;
+use ostd::{
+    arch::kexec::crash::{self, Elf64Ehdr, Elf64Phdr, PT_LOAD, PT_NOTE},
+    mm::Paddr,
+};
+use spin::Once;
+
+use crate::prelude::*;
+
+/// The ELF core file of the crashed kernel.
+#[derive(Debug)]
+pub struct Vmcore {
+    /// The ELF header and the program headers
+    header: Vec<u8>,
+    /// The segments in the order of their offsets
+    segments: Vec<VmcoreSegment>,
+    /// The size of the file
+    size: usize,
+}
+
+#[derive(Debug)]
+struct VmcoreSegment {
+    offset: usize,
+    paddr: Paddr,
+    len: usize,
+}
+
+impl Vmcore {
+    fn new(core_header: &[u8]) -> Option<Self> {
+        let mut ehdr = Elf64Ehdr::from_bytes(core_header.get(..size_of::<Elf64Ehdr>())?);
+        let mut phdrs = Vec::with_capacity(ehdr.e_phnum as usize);
+        for i in 0..ehdr.e_phnum as usize {
+            let offset = ehdr.e_phoff as usize + i * size_of::<Elf64Phdr>();
+            let bytes = core_header.get(offset..offset + size_of::<Elf64Phdr>())?;
+            phdrs.push(Elf64Phdr::from_bytes(bytes));
+        }
+
+        ehdr.e_phoff = size_of::<Elf64Ehdr>() as u64;
+        ehdr.e_shoff = 0;
+        ehdr.e_shnum = 0;
+        ehdr.e_shstrndx = 0;
+        let header_len = size_of::<Elf64Ehdr>() + phdrs.len() * size_of::<Elf64Phdr>();
+
+        // Like Linux, the notes follow the headers, and the loadable segments are page-aligned.
+        let mut offset = header_len;
+        let mut segments = Vec::new();
+        for phdr in phdrs.iter_mut() {
+            if phdr.p_type != PT_NOTE {
+                continue;
+            }
+            segments.push(VmcoreSegment {
+                offset,
+                paddr: phdr.p_offset as Paddr,
+                len: phdr.p_filesz as usize,
+            });
+            phdr.p_offset = offset as u64;
+            offset += phdr.p_filesz as usize;
+        }
+        offset = offset.align_up(PAGE_SIZE);
+        for phdr in phdrs.iter_mut() {
+            if phdr.p_type == PT_NOTE {
+                continue;
+            }
+            if phdr.p_type != PT_LOAD {
+                phdr.p_offset = 0;
+                phdr.p_filesz = 0;
+                continue;
+            }
+            segments.push(VmcoreSegment {
+                offset,
+                paddr: phdr.p_offset as Paddr,
+                len: phdr.p_filesz as usize,
+            });
+            phdr.p_offset = offset as u64;
+            offset = (offset + phdr.p_filesz as usize).align_up(PAGE_SIZE);
+        }
+
+        let mut header = Vec::with_capacity(header_len);
+        header.extend_from_slice(ehdr.as_bytes());
+        for phdr in phdrs.iter() {
+            header.extend_from_slice(phdr.as_bytes());
+        }
+
+        Some(Self {
+            header,
+            segments,
+            size: offset,
+        })
+    }
+
+    /// Reads the file at `offset` to `writer`.
+    pub fn read_at(&self, offset: usize, writer: &mut VmWriter) -> Result<usize> {
+        let mut pos = offset;
+        while writer.avail() > 0 && pos < self.size {
+            let len = if pos < self.header.len() {
+                let mut reader = VmReader::from(&self.header[pos..]);
+                writer.write_fallible(&mut reader)?
+            } else if let Some(segment) = self
+                .segments
+                .iter()
+                .find(|segment| (segment.offset..segment.offset + segment.len).contains(&pos))
+            {
+                crash::read_old_memory(segment.paddr + (pos - segment.offset), writer)?
+            } else {
+                // The gaps between the segments are filled with zeros.
+                let next = self
+                    .segments
+                    .iter()
+                    .map(|segment| segment.offset)
+                    .find(|segment_offset| *segment_offset > pos)
+                    .unwrap_or(self.size);
+                writer.fill_zeros(next - pos)?
+            };
+            if len == 0 {
+                break;
+            }
+            pos += len;
+        }
+        Ok(pos - offset)
+    }
+}
+
+/// Returns the ELF core file of the crashed kernel, if the kernel is a capture kernel.
+pub fn vmcore() -> Option<&'static Vmcore> {
+    static VMCORE: Once<Option<Vmcore>> = Once::new();
+
+    VMCORE
+        .call_once(|| crash::elf_core_header().and_then(Vmcore::new))
+        .as_ref()
+}
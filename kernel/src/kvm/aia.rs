@@ -0,0 +1,302 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The AIA device of a VM, i.e., `KVM_DEV_TYPE_RISCV_AIA`.
+//!
+//! The device provides the IMSICs of the vCPUs, which are backed by the guest interrupt files
+//! (see [`ostd::arch::virt::GuestInterruptFile`]), so the MSIs are delivered to the guests
+//! without exiting the guests. The APLIC is not emulated in the kernel, so the wired interrupts
+//! should come from the user space, either as MSIs (`KVM_SIGNAL_MSI`) or as the external
+//! interrupts of the vCPUs (`KVM_INTERRUPT`).
+
+use super::{anon_metadata, vm::Vm, KVM_MAX_VCPUS};
+use crate::{
+    current_userspace,
+    events::IoEvents,
+    fs::{
+        file_handle::FileLike,
+        utils::{IoctlCmd, Metadata},
+    },
+    prelude::*,
+    process::signal::{PollHandle, Pollable},
+};
+
+/// The device type of the AIA device.
+pub(super) const KVM_DEV_TYPE_RISCV_AIA: u32 = 11;
+
+/// `struct kvm_device_attr` in Linux.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct CKvmDeviceAttr {
+    flags: u32,
+    group: u32,
+    attr: u64,
+    addr: u64,
+}
+
+// The attribute groups and the attributes.
+const KVM_DEV_RISCV_AIA_GRP_CONFIG: u32 = 0;
+const KVM_DEV_RISCV_AIA_CONFIG_MODE: u64 = 0;
+const KVM_DEV_RISCV_AIA_CONFIG_IDS: u64 = 1;
+const KVM_DEV_RISCV_AIA_CONFIG_SRCS: u64 = 2;
+const KVM_DEV_RISCV_AIA_CONFIG_GROUP_BITS: u64 = 3;
+const KVM_DEV_RISCV_AIA_CONFIG_GROUP_SHIFT: u64 = 4;
+const KVM_DEV_RISCV_AIA_CONFIG_HART_BITS: u64 = 5;
+const KVM_DEV_RISCV_AIA_CONFIG_GUEST_BITS: u64 = 6;
+
+const KVM_DEV_RISCV_AIA_GRP_ADDR: u32 = 1;
+const KVM_DEV_RISCV_AIA_ADDR_APLIC: u64 = 0;
+/// The address of the IMSIC of vCPU `N` is attribute `N + 1`.
+const KVM_DEV_RISCV_AIA_ADDR_IMSIC_BASE: u64 = 1;
+
+const KVM_DEV_RISCV_AIA_GRP_CTRL: u32 = 2;
+const KVM_DEV_RISCV_AIA_CTRL_INIT: u64 = 0;
+
+// The modes of the IMSICs.
+const KVM_DEV_RISCV_AIA_MODE_EMUL: u32 = 0;
+const KVM_DEV_RISCV_AIA_MODE_HWACCEL: u32 = 1;
+const KVM_DEV_RISCV_AIA_MODE_AUTO: u32 = 2;
+
+/// The minimum and maximum numbers of the interrupt identities of an IMSIC.
+const IMSIC_MIN_IDS: u32 = 63;
+const IMSIC_MAX_IDS: u32 = 2047;
+
+/// The configuration of the AIA device.
+pub(super) struct AiaState {
+    mode: u32,
+    nr_ids: u32,
+    /// The geometry of the IMSIC addresses, which is only kept for the user space.
+    group_bits: u32,
+    group_shift: u32,
+    hart_bits: u32,
+    guest_bits: u32,
+    /// The guest physical addresses of the IMSICs of the vCPUs.
+    imsic_gpas: BTreeMap<u32, usize>,
+    is_initialized: bool,
+}
+
+impl AiaState {
+    pub(super) fn new() -> Self {
+        Self {
+            mode: KVM_DEV_RISCV_AIA_MODE_HWACCEL,
+            nr_ids: IMSIC_MIN_IDS,
+            group_bits: 0,
+            group_shift: 24,
+            hart_bits: 0,
+            guest_bits: 0,
+            imsic_gpas: BTreeMap::new(),
+            is_initialized: false,
+        }
+    }
+
+    /// Returns the guest physical address of the IMSIC of the vCPU.
+    ///
+    /// It returns `None` if the device has not been initialized.
+    pub(super) fn imsic_gpa(&self, vcpu_id: u32) -> Option<usize> {
+        if !self.is_initialized {
+            return None;
+        }
+        self.imsic_gpas.get(&vcpu_id).copied()
+    }
+
+    /// Returns the vCPU whose IMSIC is at the page of the guest physical address.
+    pub(super) fn imsic_vcpu(&self, gpa: usize) -> Option<u32> {
+        if !self.is_initialized {
+            return None;
+        }
+        let page = gpa & !(PAGE_SIZE - 1);
+        self.imsic_gpas
+            .iter()
+            .find(|(_, imsic_gpa)| **imsic_gpa == page)
+            .map(|(vcpu_id, _)| *vcpu_id)
+    }
+
+    fn config_mut(&mut self, attr: u64) -> Option<&mut u32> {
+        let config = match attr {
+            KVM_DEV_RISCV_AIA_CONFIG_MODE => &mut self.mode,
+            KVM_DEV_RISCV_AIA_CONFIG_IDS => &mut self.nr_ids,
+            KVM_DEV_RISCV_AIA_CONFIG_GROUP_BITS => &mut self.group_bits,
+            KVM_DEV_RISCV_AIA_CONFIG_GROUP_SHIFT => &mut self.group_shift,
+            KVM_DEV_RISCV_AIA_CONFIG_HART_BITS => &mut self.hart_bits,
+            KVM_DEV_RISCV_AIA_CONFIG_GUEST_BITS => &mut self.guest_bits,
+            _ => return None,
+        };
+        Some(config)
+    }
+
+    fn set_config(&mut self, attr: u64, value: u32) -> Result<()> {
+        match attr {
+            KVM_DEV_RISCV_AIA_CONFIG_MODE => match value {
+                KVM_DEV_RISCV_AIA_MODE_EMUL => {
+                    return_errno_with_message!(
+                        Errno::EINVAL,
+                        "the emulated IMSICs are not supported"
+                    )
+                }
+                KVM_DEV_RISCV_AIA_MODE_HWACCEL | KVM_DEV_RISCV_AIA_MODE_AUTO => {
+                    self.mode = KVM_DEV_RISCV_AIA_MODE_HWACCEL;
+                    return Ok(());
+                }
+                _ => return_errno_with_message!(Errno::EINVAL, "the IMSIC mode is invalid"),
+            },
+            KVM_DEV_RISCV_AIA_CONFIG_IDS => {
+                if !(IMSIC_MIN_IDS..=IMSIC_MAX_IDS).contains(&value) || (value + 1) % 64 != 0 {
+                    return_errno_with_message!(Errno::EINVAL, "the number of IDs is invalid");
+                }
+            }
+            // The APLIC is not emulated, so there can be no wired interrupt sources.
+            KVM_DEV_RISCV_AIA_CONFIG_SRCS => {
+                if value != 0 {
+                    return_errno_with_message!(Errno::EINVAL, "the APLIC is not supported");
+                }
+                return Ok(());
+            }
+            _ => {}
+        }
+
+        let Some(config) = self.config_mut(attr) else {
+            return_errno_with_message!(Errno::ENXIO, "the attribute is unknown");
+        };
+        *config = value;
+        Ok(())
+    }
+
+    fn get_config(&mut self, attr: u64) -> Result<u32> {
+        if attr == KVM_DEV_RISCV_AIA_CONFIG_SRCS {
+            return Ok(0);
+        }
+        self.config_mut(attr)
+            .map(|config| *config)
+            .ok_or_else(|| Error::with_message(Errno::ENXIO, "the attribute is unknown"))
+    }
+}
+
+/// The file of the AIA device.
+pub(super) struct AiaDevice {
+    vm: Arc<Vm>,
+}
+
+impl AiaDevice {
+    pub(super) fn new(vm: Arc<Vm>) -> Self {
+        Self { vm }
+    }
+
+    fn set_attr(&self, attr: &CKvmDeviceAttr) -> Result<()> {
+        let user_space = current_userspace!();
+        let mut aia = self.vm.aia().lock();
+        let state = aia.as_mut().unwrap();
+
+        if state.is_initialized {
+            return_errno_with_message!(Errno::EBUSY, "the AIA device has been initialized");
+        }
+
+        match (attr.group, attr.attr) {
+            (KVM_DEV_RISCV_AIA_GRP_CONFIG, _) => {
+                let value: u32 = user_space.read_val(attr.addr as Vaddr)?;
+                state.set_config(attr.attr, value)?;
+            }
+            (KVM_DEV_RISCV_AIA_GRP_ADDR, KVM_DEV_RISCV_AIA_ADDR_APLIC) => {
+                return_errno_with_message!(Errno::ENXIO, "the APLIC is not supported")
+            }
+            (KVM_DEV_RISCV_AIA_GRP_ADDR, _) => {
+                let vcpu_id = imsic_vcpu_id(attr.attr)?;
+                let gpa: u64 = user_space.read_val(attr.addr as Vaddr)?;
+                if gpa as usize % PAGE_SIZE != 0 {
+                    return_errno_with_message!(Errno::EINVAL, "the IMSIC is not page-aligned");
+                }
+                state.imsic_gpas.insert(vcpu_id, gpa as usize);
+            }
+            (KVM_DEV_RISCV_AIA_GRP_CTRL, KVM_DEV_RISCV_AIA_CTRL_INIT) => {
+                if self
+                    .vm
+                    .vcpu_ids()
+                    .iter()
+                    .any(|vcpu_id| !state.imsic_gpas.contains_key(vcpu_id))
+                {
+                    return_errno_with_message!(
+                        Errno::EINVAL,
+                        "the IMSIC address of a vCPU is not set"
+                    );
+                }
+                state.is_initialized = true;
+            }
+            _ => return_errno_with_message!(Errno::ENXIO, "the attribute is unknown"),
+        }
+
+        Ok(())
+    }
+
+    fn get_attr(&self, attr: &CKvmDeviceAttr) -> Result<()> {
+        let user_space = current_userspace!();
+        let mut aia = self.vm.aia().lock();
+        let state = aia.as_mut().unwrap();
+
+        match attr.group {
+            KVM_DEV_RISCV_AIA_GRP_CONFIG => {
+                let value = state.get_config(attr.attr)?;
+                user_space.write_val(attr.addr as Vaddr, &value)?;
+            }
+            KVM_DEV_RISCV_AIA_GRP_ADDR if attr.attr != KVM_DEV_RISCV_AIA_ADDR_APLIC => {
+                let vcpu_id = imsic_vcpu_id(attr.attr)?;
+                let gpa = state.imsic_gpas.get(&vcpu_id).copied().unwrap_or(0);
+                user_space.write_val(attr.addr as Vaddr, &(gpa as u64))?;
+            }
+            _ => return_errno_with_message!(Errno::ENXIO, "the attribute is unknown"),
+        }
+
+        Ok(())
+    }
+
+    fn has_attr(&self, attr: &CKvmDeviceAttr) -> Result<()> {
+        let is_supported = match attr.group {
+            KVM_DEV_RISCV_AIA_GRP_CONFIG => (KVM_DEV_RISCV_AIA_CONFIG_MODE
+                ..=KVM_DEV_RISCV_AIA_CONFIG_GUEST_BITS)
+                .contains(&attr.attr),
+            KVM_DEV_RISCV_AIA_GRP_ADDR => {
+                attr.attr != KVM_DEV_RISCV_AIA_ADDR_APLIC && imsic_vcpu_id(attr.attr).is_ok()
+            }
+            KVM_DEV_RISCV_AIA_GRP_CTRL => attr.attr == KVM_DEV_RISCV_AIA_CTRL_INIT,
+            _ => false,
+        };
+        if !is_supported {
+            return_errno_with_message!(Errno::ENXIO, "the attribute is unknown");
+        }
+        Ok(())
+    }
+}
+
+/// Returns the vCPU of an IMSIC address attribute.
+fn imsic_vcpu_id(attr: u64) -> Result<u32> {
+    match attr.checked_sub(KVM_DEV_RISCV_AIA_ADDR_IMSIC_BASE) {
+        Some(vcpu_id) if vcpu_id < KVM_MAX_VCPUS as u64 => Ok(vcpu_id as u32),
+        _ => return_errno_with_message!(Errno::ENXIO, "the vCPU of the IMSIC is invalid"),
+    }
+}
+
+impl Pollable for AiaDevice {
+    fn poll(&self, mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        let events = IoEvents::IN | IoEvents::OUT;
+        events & mask
+    }
+}
+
+impl FileLike for AiaDevice {
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        let handle_attr = match cmd {
+            IoctlCmd::KVM_SET_DEVICE_ATTR => Self::set_attr,
+            IoctlCmd::KVM_GET_DEVICE_ATTR => Self::get_attr,
+            IoctlCmd::KVM_HAS_DEVICE_ATTR => Self::has_attr,
+            _ => return_errno_with_message!(Errno::ENOTTY, "the ioctl command is unknown"),
+        };
+
+        let attr: CKvmDeviceAttr = current_userspace!().read_val(arg)?;
+        if attr.flags != 0 {
+            return_errno_with_message!(Errno::EINVAL, "the attribute flags are invalid");
+        }
+        handle_attr(self, &attr)?;
+        Ok(0)
+    }
+
+    fn metadata(&self) -> Metadata {
+        anon_metadata()
+    }
+}
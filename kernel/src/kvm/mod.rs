@@ -0,0 +1,170 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The KVM-like API for running guest VMs on RISC-V, i.e., `/dev/kvm`.
+//!
+//! The API follows that of Linux: `KVM_CREATE_VM` on `/dev/kvm` creates a VM file, on which
+//! `KVM_SET_USER_MEMORY_REGION` maps the user memory as the guest memory and `KVM_CREATE_VCPU`
+//! creates a vCPU file. `KVM_RUN` on a vCPU file runs the guest until it needs the help of the
+//! user space (e.g., to emulate an MMIO access or an SBI call), which is described in the
+//! `struct kvm_run` mapped from the vCPU file. The guests are run by OSTD with the hypervisor
+//! extension (see [`ostd::arch::virt`]).
+//!
+//! The SBI calls of the guests are mostly emulated in the kernel, including those that start
+//! and stop the vCPUs and send the IPIs between them. The other SBI calls exit to the user space
+//! with `KVM_EXIT_RISCV_SBI` (see the `vcpu::sbi` module). As in Linux, only vCPU 0 runs at first.
+//!
+//! The timer of a vCPU is virtualized with the time delta of the guest, and its interrupt is
+//! injected when the guest runs. The external interrupts come from the devices emulated by the
+//! user space: the wired interrupts (e.g., of an emulated PLIC) are injected by `KVM_INTERRUPT`,
+//! and the MSIs are sent by `KVM_SIGNAL_MSI` to the guest interrupt files, which back the IMSICs
+//! of the AIA device (`KVM_CREATE_DEVICE`) if the hardware has the AIA.
+//!
+//! Compared to Linux, there are some limitations:
+//!  - The user pages are pinned when the memory region is set, so the guest memory does not
+//!    follow the later changes of the mappings (e.g., `munmap` or copy-on-write after `fork`).
+//!  - Only the core registers, the CSRs and the timer registers are accessible by
+//!    `KVM_GET_ONE_REG` and `KVM_SET_ONE_REG`, and the APLIC is not emulated.
+//!  - The injected interrupts are taken when the guest exits and runs again, since the vCPU is
+//!    not kicked out of the guest by an inter-processor interrupt.
+//!  - A vCPU with an IMSIC is bound to the CPU where it first runs, since its guest interrupt
+//!    file belongs to the CPU.
+//!  - The MMIO accesses can only be emulated if the hardware provides the trapped instructions
+//!    in `htinst`.
+//!
+//! Reference: <https://docs.kernel.org/virt/kvm/api.html>
+
+mod aia;
+mod vcpu;
+mod vm;
+
+use ostd::arch::virt::nr_guest_files;
+pub use vcpu::Vcpu;
+use vm::{Vm, KVM_USER_MEM_SLOTS};
+
+use crate::{
+    events::IoEvents,
+    fs::{
+        device::{Device, DeviceId, DeviceType},
+        file_handle::FileLike,
+        file_table::FdFlags,
+        inode_handle::FileIo,
+        utils::{InodeMode, InodeType, IoctlCmd, Metadata},
+    },
+    prelude::*,
+    process::{
+        posix_thread::AsThreadLocal,
+        signal::{PollHandle, Pollable},
+        Gid, Uid,
+    },
+    time::clocks::RealTimeClock,
+};
+
+/// The version of the API, which is always 12 since Linux 2.6.22.
+const KVM_API_VERSION: i32 = 12;
+
+/// The maximum number of the vCPUs in a VM.
+const KVM_MAX_VCPUS: u32 = 64;
+
+// The capabilities that can be checked by `KVM_CHECK_EXTENSION`.
+const KVM_CAP_USER_MEMORY: usize = 3;
+const KVM_CAP_NR_VCPUS: usize = 9;
+const KVM_CAP_NR_MEMSLOTS: usize = 10;
+const KVM_CAP_ONE_REG: usize = 70;
+const KVM_CAP_SIGNAL_MSI: usize = 77;
+const KVM_CAP_DEVICE_CTRL: usize = 89;
+
+/// Corresponds to `/dev/kvm` in the file system.
+pub struct KvmDevice;
+
+impl Device for KvmDevice {
+    fn type_(&self) -> DeviceType {
+        DeviceType::MiscDevice
+    }
+
+    fn id(&self) -> DeviceId {
+        DeviceId::new(10, 232)
+    }
+
+    fn open(&self) -> Result<Option<Arc<dyn FileIo>>> {
+        Ok(Some(Arc::new(KvmDevice)))
+    }
+}
+
+impl Pollable for KvmDevice {
+    fn poll(&self, mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        let events = IoEvents::IN | IoEvents::OUT;
+        events & mask
+    }
+}
+
+impl FileIo for KvmDevice {
+    fn read(&self, _writer: &mut VmWriter) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "cannot read the KVM device");
+    }
+
+    fn write(&self, _reader: &mut VmReader) -> Result<usize> {
+        return_errno_with_message!(Errno::EINVAL, "cannot write the KVM device");
+    }
+
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        match cmd {
+            IoctlCmd::KVM_GET_API_VERSION => Ok(KVM_API_VERSION),
+            IoctlCmd::KVM_CREATE_VM => {
+                // Only the default machine type is supported.
+                if arg != 0 {
+                    return_errno_with_message!(Errno::EINVAL, "the machine type is invalid");
+                }
+                insert_file(Vm::new()?)
+            }
+            IoctlCmd::KVM_CHECK_EXTENSION => Ok(check_extension(arg)),
+            IoctlCmd::KVM_GET_VCPU_MMAP_SIZE => Ok(PAGE_SIZE as i32),
+            _ => return_errno_with_message!(Errno::ENOTTY, "the ioctl command is unknown"),
+        }
+    }
+}
+
+/// Returns whether the capability is supported, or its value if it is a number.
+fn check_extension(cap: usize) -> i32 {
+    match cap {
+        KVM_CAP_USER_MEMORY | KVM_CAP_ONE_REG => 1,
+        KVM_CAP_NR_VCPUS => KVM_MAX_VCPUS as i32,
+        KVM_CAP_NR_MEMSLOTS => KVM_USER_MEM_SLOTS as i32,
+        // The MSIs can only be delivered to the guest interrupt files.
+        KVM_CAP_SIGNAL_MSI | KVM_CAP_DEVICE_CTRL => (nr_guest_files() > 0) as i32,
+        _ => 0,
+    }
+}
+
+/// Inserts a VM file, a vCPU file or a device file into the file table of the current thread.
+///
+/// The files are always opened with `O_CLOEXEC`, as Linux does.
+fn insert_file(file: Arc<dyn FileLike>) -> Result<i32> {
+    let task = ostd::task::Task::current().unwrap();
+    let file_table = task.as_thread_local().unwrap().file_table().borrow();
+    let fd = file_table.write().insert(file, FdFlags::CLOEXEC)?;
+    Ok(fd)
+}
+
+/// Returns the metadata of the VM files, the vCPU files and the device files.
+fn anon_metadata() -> Metadata {
+    // This is a dummy implementation.
+    // TODO: Add "anonymous inode fs" and link the files to it.
+    let now = RealTimeClock::get().read_time();
+    Metadata {
+        dev: 0,
+        ino: 0,
+        size: 0,
+        blk_size: 0,
+        blocks: 0,
+        atime: now,
+        mtime: now,
+        ctime: now,
+        btime: None,
+        type_: InodeType::File,
+        mode: InodeMode::from_bits_truncate(0o600),
+        nlinks: 1,
+        uid: Uid::new_root(),
+        gid: Gid::new_root(),
+        rdev: 0,
+    }
+}
@@ -0,0 +1,680 @@
+// SPDX-License-Identifier: MPL-2.0
+
+mod sbi;
+
+use core::{
+    mem::size_of,
+    sync::atomic::{AtomicU8, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use ostd::{
+    arch::{
+        read_tsc,
+        trap::GeneralRegs,
+        tsc_freq,
+        virt::{GuestContext, GuestInterruptFile, MmioInsn, VirtualInterrupt, VmExit},
+    },
+    cpu::CpuSet,
+    mm::VmIo,
+    sync::WaitQueue,
+};
+
+use self::sbi::{HartState, SbiExt, SbiExts};
+use super::{anon_metadata, vm::Vm};
+use crate::{
+    current_userspace,
+    events::IoEvents,
+    fs::{
+        file_handle::FileLike,
+        utils::{IoctlCmd, Metadata},
+    },
+    prelude::*,
+    process::{
+        posix_thread::AsPosixThread,
+        signal::{PollHandle, Pollable},
+    },
+    thread::Thread,
+    vm::vmo::{Vmo, VmoOptions},
+};
+
+// The offsets of the fields in `struct kvm_run`.
+const IMMEDIATE_EXIT_OFFSET: usize = 1;
+const EXIT_REASON_OFFSET: usize = 8;
+/// The offset of the union that describes the exit.
+const EXIT_INFO_OFFSET: usize = 32;
+
+const KVM_EXIT_MMIO: u32 = 6;
+const KVM_EXIT_INTR: u32 = 10;
+const KVM_EXIT_INTERNAL_ERROR: u32 = 17;
+const KVM_EXIT_SYSTEM_EVENT: u32 = 24;
+const KVM_EXIT_RISCV_SBI: u32 = 35;
+
+const KVM_INTERNAL_ERROR_EMULATION: u32 = 1;
+
+/// The `mmio` member of the union in `struct kvm_run`.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct CKvmRunMmio {
+    phys_addr: u64,
+    data: [u8; 8],
+    len: u32,
+    is_write: u8,
+    _pad: [u8; 3],
+}
+
+/// The `riscv_sbi` member of the union in `struct kvm_run`.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct CKvmRunSbi {
+    extension_id: u64,
+    function_id: u64,
+    args: [u64; 6],
+    ret: [u64; 2],
+}
+
+/// The `internal` member of the union in `struct kvm_run`, without the data.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct CKvmRunInternal {
+    suberror: u32,
+    ndata: u32,
+}
+
+/// `struct kvm_one_reg` in Linux.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct CKvmOneReg {
+    id: u64,
+    addr: u64,
+}
+
+/// The values of `struct kvm_interrupt` that set and clear the external interrupt.
+const KVM_INTERRUPT_SET: u32 = u32::MAX;
+const KVM_INTERRUPT_UNSET: u32 = u32::MAX - 1;
+
+// The values of `struct kvm_mp_state`.
+const KVM_MP_STATE_RUNNABLE: u32 = 0;
+const KVM_MP_STATE_STOPPED: u32 = 5;
+
+// The fields of the register IDs.
+const KVM_REG_ARCH_MASK: u64 = 0xff00_0000_0000_0000;
+const KVM_REG_RISCV: u64 = 0x8000_0000_0000_0000;
+const KVM_REG_SIZE_MASK: u64 = 0x00f0_0000_0000_0000;
+const KVM_REG_SIZE_U64: u64 = 0x0030_0000_0000_0000;
+const KVM_REG_RISCV_TYPE_MASK: u64 = 0xff00_0000;
+const KVM_REG_RISCV_CORE: u64 = 2 << 24;
+const KVM_REG_RISCV_CSR: u64 = 3 << 24;
+const KVM_REG_RISCV_TIMER: u64 = 4 << 24;
+const KVM_REG_RISCV_SBI_EXT: u64 = 5 << 24;
+const KVM_REG_RISCV_SUBTYPE_MASK: u64 = 0x00ff_0000;
+const KVM_REG_RISCV_SBI_SINGLE: u64 = 0;
+const KVM_REG_RISCV_INDEX_MASK: u64 = 0x00ff_ffff;
+
+/// The index of `mode` in `struct kvm_riscv_core`, which follows the 32 registers.
+const CORE_MODE_INDEX: usize = 32;
+/// The value of `mode` for the supervisor mode.
+const KVM_RISCV_MODE_S: usize = 1;
+
+// The indexes in `struct kvm_riscv_csr`.
+const CSR_SSTATUS: usize = 0;
+const CSR_SIE: usize = 1;
+const CSR_STVEC: usize = 2;
+const CSR_SSCRATCH: usize = 3;
+const CSR_SEPC: usize = 4;
+const CSR_SCAUSE: usize = 5;
+const CSR_STVAL: usize = 6;
+const CSR_SIP: usize = 7;
+const CSR_SATP: usize = 8;
+
+// The indexes in `struct kvm_riscv_timer`.
+const TIMER_FREQUENCY: usize = 0;
+const TIMER_TIME: usize = 1;
+const TIMER_COMPARE: usize = 2;
+const TIMER_STATE: usize = 3;
+
+const KVM_RISCV_TIMER_STATE_OFF: usize = 0;
+const KVM_RISCV_TIMER_STATE_ON: usize = 1;
+
+const SSTATUS_SIE: usize = 1 << 1;
+
+// The bits of `sip` (and `sie`).
+const SIP_SSIP: usize = 1 << 1;
+const SIP_STIP: usize = 1 << 5;
+const SIP_SEIP: usize = 1 << 9;
+
+/// The encoding of `wfi`.
+const INSN_WFI: usize = 0x1050_0073;
+
+/// A vCPU of a VM.
+pub struct Vcpu {
+    vm: Arc<Vm>,
+    id: u32,
+    /// The VMO of `struct kvm_run`, which is shared with the user space.
+    run: Vmo,
+    state: Mutex<VcpuState>,
+    /// The software and external interrupts (as the bits of `sip`) that are injected by the
+    /// user space, which can be done while the guest is running.
+    injected_irqs: AtomicUsize,
+    /// The fences (see [`sbi::RemoteFences`]) that are requested by the other vCPUs and are
+    /// done before the guest runs again.
+    remote_fences: AtomicU8,
+    hart_state: SpinLock<HartState>,
+    /// The queue where the vCPU waits for the interrupts after the guest executes `wfi`.
+    wfi_queue: WaitQueue,
+    /// The guest interrupt file, which is allocated when the guest first runs if the guest has
+    /// an IMSIC.
+    interrupt_file: Mutex<Option<Arc<GuestInterruptFile>>>,
+}
+
+struct VcpuState {
+    ctx: GuestContext,
+    /// The exit whose result should be taken from `struct kvm_run` when the guest runs again.
+    pending: Option<PendingExit>,
+    /// The time of the guest (in the ticks of the `time` CSR) when the timer interrupt fires.
+    timer_compare: u64,
+    is_timer_on: bool,
+    sbi_exts: SbiExts,
+    /// Whether the guest has run, after which the SBI extensions cannot be changed.
+    has_run: bool,
+}
+
+impl VcpuState {
+    /// Returns the time of the guest, which is the host time plus the time delta.
+    fn guest_time(&self) -> u64 {
+        read_tsc().wrapping_add(self.ctx.time_delta() as u64)
+    }
+
+    fn is_timer_expired(&self) -> bool {
+        self.is_timer_on && self.guest_time() >= self.timer_compare
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum PendingExit {
+    Mmio(MmioInsn),
+    Sbi,
+}
+
+/// A register that is specified by `KVM_GET_ONE_REG` and `KVM_SET_ONE_REG`.
+#[derive(Debug, Clone, Copy)]
+enum VcpuReg {
+    Pc,
+    General(usize),
+    Mode,
+    Csr(usize),
+    Timer(usize),
+    SbiExt(usize),
+}
+
+impl Vcpu {
+    pub(super) fn new(vm: Arc<Vm>, id: u32) -> Result<Arc<Self>> {
+        let mut ctx = GuestContext::new();
+        // The guest kernel finds its hart ID in `a0`, as the SBI firmware passes.
+        ctx.general_regs_mut().a0 = id as usize;
+
+        Ok(Arc::new(Self {
+            vm,
+            id,
+            run: VmoOptions::<Rights>::new(PAGE_SIZE).alloc()?,
+            state: Mutex::new(VcpuState {
+                ctx,
+                pending: None,
+                timer_compare: u64::MAX,
+                is_timer_on: false,
+                sbi_exts: SbiExts::new(),
+                has_run: false,
+            }),
+            injected_irqs: AtomicUsize::new(0),
+            remote_fences: AtomicU8::new(0),
+            // vCPU 0 is the boot hart, and the others are started by the SBI HSM extension,
+            // as Linux does.
+            hart_state: SpinLock::new(if id == 0 {
+                HartState::Started
+            } else {
+                HartState::Stopped
+            }),
+            wfi_queue: WaitQueue::new(),
+            interrupt_file: Mutex::new(None),
+        }))
+    }
+
+    /// Returns the VMO of `struct kvm_run` to be mapped to the user space.
+    pub fn mmap_vmo(&self, len: usize) -> Result<Vmo> {
+        if len > PAGE_SIZE {
+            return_errno_with_message!(Errno::EINVAL, "the mapping is larger than `kvm_run`");
+        }
+        self.run.dup()
+    }
+
+    /// Runs the guest until it exits to the user space, as done by `KVM_RUN`.
+    fn run(&self) -> Result<i32> {
+        if self.run.read_val::<u8>(IMMEDIATE_EXIT_OFFSET)? != 0 {
+            self.set_exit_reason(KVM_EXIT_INTR)?;
+            return_errno_with_message!(Errno::EINTR, "the immediate exit is requested");
+        }
+
+        let current = current_thread!();
+        let posix_thread = current.as_posix_thread().unwrap();
+
+        let mut state = self.state.lock();
+        self.complete_pending_exit(&mut state)?;
+        self.init_interrupt_file(&mut state)?;
+        state.has_run = true;
+
+        loop {
+            if posix_thread.has_pending() {
+                self.set_exit_reason(KVM_EXIT_INTR)?;
+                return_errno_with_message!(Errno::EINTR, "a signal is pending");
+            }
+            if let Err(err) = self.wait_until_started(&mut state) {
+                self.set_exit_reason(KVM_EXIT_INTR)?;
+                return Err(err);
+            }
+
+            // The software interrupt is injected once, and is cleared by the guest after the
+            // guest takes it.
+            let injected_irqs = self.injected_irqs.fetch_and(!SIP_SSIP, Ordering::Relaxed);
+            let is_timer_expired = state.is_timer_expired();
+            let ctx = &mut state.ctx;
+            if injected_irqs & SIP_SSIP != 0 {
+                ctx.set_interrupt_pending(VirtualInterrupt::Software, true);
+            }
+            ctx.set_interrupt_pending(VirtualInterrupt::Timer, is_timer_expired);
+            ctx.set_interrupt_pending(VirtualInterrupt::External, injected_irqs & SIP_SEIP != 0);
+            self.take_remote_fences(ctx);
+
+            let exit = state.ctx.run(self.vm.page_table());
+            if let Some(exit_reason) = self.handle_exit(&mut state, exit)? {
+                self.set_exit_reason(exit_reason)?;
+                return Ok(0);
+            }
+        }
+    }
+
+    /// Allocates the guest interrupt file on the current CPU and maps it as the IMSIC of the
+    /// guest, if the guest has an IMSIC and the file has not been allocated.
+    ///
+    /// The thread is bound to the CPU, since the file can only deliver the interrupts to the
+    /// guest running on the CPU.
+    fn init_interrupt_file(&self, state: &mut VcpuState) -> Result<()> {
+        let Some(imsic_gpa) = self.vm.imsic_gpa(self.id) else {
+            return Ok(());
+        };
+        let mut interrupt_file = self.interrupt_file.lock();
+        if interrupt_file.is_some() {
+            return Ok(());
+        }
+
+        let cpu = ostd::cpu::current_cpu_racy();
+        let file = Arc::new(GuestInterruptFile::alloc(cpu)?);
+        self.vm
+            .page_table()
+            .map_interrupt_file(imsic_gpa, file.clone())?;
+        let mut cpu_set = CpuSet::new_empty();
+        cpu_set.add(cpu);
+        current_thread!().atomic_cpu_affinity().store(&cpu_set);
+
+        state.ctx.set_interrupt_file(Some(&file));
+        *interrupt_file = Some(file);
+        Ok(())
+    }
+
+    /// Sends an MSI to the guest interrupt file, as done by `KVM_SIGNAL_MSI`.
+    pub(super) fn send_msi(&self, eid: u32) -> Result<()> {
+        let Some(file) = self.interrupt_file.lock().clone() else {
+            return_errno_with_message!(Errno::EINVAL, "the guest interrupt file is not ready");
+        };
+        file.send(eid)?;
+        self.wfi_queue.wake_all();
+        Ok(())
+    }
+
+    /// Sets or clears the external interrupt, as done by `KVM_INTERRUPT`.
+    fn set_external_irq(&self, irq: u32) -> Result<()> {
+        match irq {
+            KVM_INTERRUPT_SET => self.injected_irqs.fetch_or(SIP_SEIP, Ordering::Relaxed),
+            KVM_INTERRUPT_UNSET => self.injected_irqs.fetch_and(!SIP_SEIP, Ordering::Relaxed),
+            _ => return_errno_with_message!(Errno::EINVAL, "the interrupt is invalid"),
+        };
+        self.wfi_queue.wake_all();
+        Ok(())
+    }
+
+    /// Waits until an interrupt that is enabled by the guest becomes pending, which is done
+    /// after the guest executes `wfi`.
+    ///
+    /// The waiting is also interrupted by the signals, which are checked before the guest runs
+    /// again.
+    fn wait_for_interrupt(&self, state: &VcpuState) {
+        let vsie = state.ctx.vs_csrs().vsie;
+        let interrupt_file = self.interrupt_file.lock().clone();
+        let has_interrupt = || {
+            let mut pending = self.injected_irqs.load(Ordering::Relaxed);
+            if state.ctx.is_interrupt_pending(VirtualInterrupt::Software) {
+                pending |= SIP_SSIP;
+            }
+            if state.is_timer_expired() {
+                pending |= SIP_STIP;
+            }
+            if interrupt_file
+                .as_ref()
+                .is_some_and(|file| file.is_pending())
+            {
+                pending |= SIP_SEIP;
+            }
+            (pending & vsie != 0).then_some(())
+        };
+
+        let timeout = if state.is_timer_on {
+            let ticks = state.timer_compare.saturating_sub(state.guest_time());
+            let nanos = ticks as u128 * 1_000_000_000 / tsc_freq() as u128;
+            Some(Duration::from_nanos(nanos.min(u64::MAX as u128) as u64))
+        } else {
+            None
+        };
+        let _ = self
+            .wfi_queue
+            .pause_until_or_timeout(has_interrupt, timeout.as_ref());
+    }
+
+    /// Takes the result of the last exit from `struct kvm_run`.
+    fn complete_pending_exit(&self, state: &mut VcpuState) -> Result<()> {
+        match state.pending.take() {
+            None => {}
+            Some(PendingExit::Mmio(mmio)) => {
+                if !mmio.is_write {
+                    let info: CKvmRunMmio = self.run.read_val(EXIT_INFO_OFFSET)?;
+                    let value = u64::from_le_bytes(info.data);
+                    mmio.complete_load(state.ctx.general_regs_mut(), value);
+                }
+                let pc = state.ctx.pc() + mmio.len;
+                state.ctx.set_pc(pc);
+            }
+            Some(PendingExit::Sbi) => {
+                let info: CKvmRunSbi = self.run.read_val(EXIT_INFO_OFFSET)?;
+                let regs = state.ctx.general_regs_mut();
+                regs.a0 = info.ret[0] as usize;
+                regs.a1 = info.ret[1] as usize;
+            }
+        }
+        Ok(())
+    }
+
+    /// Handles a VM exit, and returns the exit reason if the user space should handle it.
+    fn handle_exit(&self, state: &mut VcpuState, exit: VmExit) -> Result<Option<u32>> {
+        match exit {
+            VmExit::SbiCall => self.handle_sbi_call(state),
+            VmExit::GuestPageFault {
+                gpa,
+                mmio: Some(mmio),
+                ..
+            } => {
+                let data = if mmio.is_write {
+                    mmio.store_value(state.ctx.general_regs()).to_le_bytes()
+                } else {
+                    [0; 8]
+                };
+                let info = CKvmRunMmio {
+                    phys_addr: gpa as u64,
+                    data,
+                    len: mmio.width as u32,
+                    is_write: mmio.is_write as u8,
+                    _pad: [0; 3],
+                };
+                self.run.write_val(EXIT_INFO_OFFSET, &info)?;
+                state.pending = Some(PendingExit::Mmio(mmio));
+                Ok(Some(KVM_EXIT_MMIO))
+            }
+            VmExit::VirtualInstruction { insn: INSN_WFI } => {
+                let pc = state.ctx.pc() + 4;
+                state.ctx.set_pc(pc);
+                self.wait_for_interrupt(state);
+                Ok(None)
+            }
+            VmExit::Interrupt { .. } => {
+                Thread::yield_now();
+                Ok(None)
+            }
+            VmExit::GuestPageFault { .. }
+            | VmExit::VirtualInstruction { .. }
+            | VmExit::Exception { .. } => {
+                debug!(
+                    "the VM exit cannot be handled: {:?}, pc = {:#x}",
+                    exit,
+                    state.ctx.pc()
+                );
+                let info = CKvmRunInternal {
+                    suberror: KVM_INTERNAL_ERROR_EMULATION,
+                    ndata: 0,
+                };
+                self.run.write_val(EXIT_INFO_OFFSET, &info)?;
+                Ok(Some(KVM_EXIT_INTERNAL_ERROR))
+            }
+        }
+    }
+
+    fn set_exit_reason(&self, exit_reason: u32) -> Result<()> {
+        self.run.write_val(EXIT_REASON_OFFSET, &exit_reason)?;
+        Ok(())
+    }
+
+    fn get_reg(&self, reg: VcpuReg) -> Result<usize> {
+        let state = self.state.lock();
+        let ctx = &state.ctx;
+        let csrs = ctx.vs_csrs();
+
+        let value = match reg {
+            VcpuReg::Pc => ctx.pc(),
+            VcpuReg::General(index) => read_general_reg(ctx.general_regs(), index),
+            VcpuReg::Mode => KVM_RISCV_MODE_S,
+            VcpuReg::Csr(CSR_SSTATUS) => csrs.vsstatus,
+            VcpuReg::Csr(CSR_SIE) => csrs.vsie,
+            VcpuReg::Csr(CSR_STVEC) => csrs.vstvec,
+            VcpuReg::Csr(CSR_SSCRATCH) => csrs.vsscratch,
+            VcpuReg::Csr(CSR_SEPC) => csrs.vsepc,
+            VcpuReg::Csr(CSR_SCAUSE) => csrs.vscause,
+            VcpuReg::Csr(CSR_STVAL) => csrs.vstval,
+            VcpuReg::Csr(CSR_SIP) => {
+                let mut sip = self.injected_irqs.load(Ordering::Relaxed);
+                if ctx.is_interrupt_pending(VirtualInterrupt::Software) {
+                    sip |= SIP_SSIP;
+                }
+                if state.is_timer_expired() {
+                    sip |= SIP_STIP;
+                }
+                sip
+            }
+            VcpuReg::Csr(CSR_SATP) => csrs.vsatp,
+            VcpuReg::Csr(_) => return_errno_with_message!(Errno::EINVAL, "the CSR is unknown"),
+            VcpuReg::Timer(TIMER_FREQUENCY) => tsc_freq() as usize,
+            VcpuReg::Timer(TIMER_TIME) => state.guest_time() as usize,
+            VcpuReg::Timer(TIMER_COMPARE) => state.timer_compare as usize,
+            VcpuReg::Timer(TIMER_STATE) if state.is_timer_on => KVM_RISCV_TIMER_STATE_ON,
+            VcpuReg::Timer(TIMER_STATE) => KVM_RISCV_TIMER_STATE_OFF,
+            VcpuReg::Timer(_) => {
+                return_errno_with_message!(Errno::EINVAL, "the timer register is unknown")
+            }
+            VcpuReg::SbiExt(index) => {
+                let Some(ext) = SbiExt::from_kvm_id(index) else {
+                    return_errno_with_message!(Errno::ENOENT, "the SBI extension is unknown");
+                };
+                state.sbi_exts.is_enabled(ext) as usize
+            }
+        };
+        Ok(value)
+    }
+
+    fn set_reg(&self, reg: VcpuReg, value: usize) -> Result<()> {
+        let mut state = self.state.lock();
+
+        match reg {
+            VcpuReg::Timer(TIMER_FREQUENCY) => {
+                return_errno_with_message!(Errno::EINVAL, "the timer frequency is read-only")
+            }
+            // The time of the guest is changed by changing the time delta.
+            VcpuReg::Timer(TIMER_TIME) => {
+                let delta = (value as u64).wrapping_sub(read_tsc());
+                state.ctx.set_time_delta(delta as usize);
+                return Ok(());
+            }
+            VcpuReg::Timer(TIMER_COMPARE) => {
+                state.timer_compare = value as u64;
+                return Ok(());
+            }
+            VcpuReg::Timer(TIMER_STATE) => {
+                state.is_timer_on = match value {
+                    KVM_RISCV_TIMER_STATE_OFF => false,
+                    KVM_RISCV_TIMER_STATE_ON => true,
+                    _ => return_errno_with_message!(Errno::EINVAL, "the timer state is invalid"),
+                };
+                return Ok(());
+            }
+            VcpuReg::Timer(_) => {
+                return_errno_with_message!(Errno::EINVAL, "the timer register is unknown")
+            }
+            VcpuReg::SbiExt(index) => {
+                let Some(ext) = SbiExt::from_kvm_id(index) else {
+                    return_errno_with_message!(Errno::ENOENT, "the SBI extension is unknown");
+                };
+                if state.has_run {
+                    return_errno_with_message!(
+                        Errno::EBUSY,
+                        "the SBI extensions cannot be changed after the guest runs"
+                    );
+                }
+                let is_enabled = match value {
+                    0 => false,
+                    1 => true,
+                    _ => return_errno_with_message!(Errno::EINVAL, "the value is invalid"),
+                };
+                state.sbi_exts.set_enabled(ext, is_enabled);
+                return Ok(());
+            }
+            _ => {}
+        }
+
+        let ctx = &mut state.ctx;
+        match reg {
+            VcpuReg::Pc => ctx.set_pc(value),
+            VcpuReg::General(index) => write_general_reg(ctx.general_regs_mut(), index, value),
+            VcpuReg::Mode if value == KVM_RISCV_MODE_S => {}
+            VcpuReg::Mode => {
+                return_errno_with_message!(Errno::EINVAL, "only the supervisor mode is supported")
+            }
+            VcpuReg::Csr(CSR_SSTATUS) => ctx.vs_csrs_mut().vsstatus = value,
+            VcpuReg::Csr(CSR_SIE) => ctx.vs_csrs_mut().vsie = value,
+            VcpuReg::Csr(CSR_STVEC) => ctx.vs_csrs_mut().vstvec = value,
+            VcpuReg::Csr(CSR_SSCRATCH) => ctx.vs_csrs_mut().vsscratch = value,
+            VcpuReg::Csr(CSR_SEPC) => ctx.vs_csrs_mut().vsepc = value,
+            VcpuReg::Csr(CSR_SCAUSE) => ctx.vs_csrs_mut().vscause = value,
+            VcpuReg::Csr(CSR_STVAL) => ctx.vs_csrs_mut().vstval = value,
+            // The timer interrupt is controlled by the SBI timer, so only the software and
+            // external interrupts can be injected by the user space.
+            VcpuReg::Csr(CSR_SIP) => {
+                ctx.set_interrupt_pending(VirtualInterrupt::Software, value & SIP_SSIP != 0);
+                self.injected_irqs
+                    .store(value & SIP_SEIP, Ordering::Relaxed);
+                self.wfi_queue.wake_all();
+            }
+            VcpuReg::Csr(CSR_SATP) => ctx.vs_csrs_mut().vsatp = value,
+            VcpuReg::Csr(_) => return_errno_with_message!(Errno::EINVAL, "the CSR is unknown"),
+            VcpuReg::Timer(_) | VcpuReg::SbiExt(_) => unreachable!(),
+        }
+        Ok(())
+    }
+}
+
+impl VcpuReg {
+    fn parse(id: u64) -> Result<Self> {
+        if id & KVM_REG_ARCH_MASK != KVM_REG_RISCV || id & KVM_REG_SIZE_MASK != KVM_REG_SIZE_U64 {
+            return_errno_with_message!(Errno::EINVAL, "the register ID is invalid");
+        }
+
+        let index = (id & KVM_REG_RISCV_INDEX_MASK) as usize;
+        let reg = match (id & KVM_REG_RISCV_TYPE_MASK, index) {
+            (KVM_REG_RISCV_CORE, 0) => Self::Pc,
+            (KVM_REG_RISCV_CORE, 1..CORE_MODE_INDEX) => Self::General(index),
+            (KVM_REG_RISCV_CORE, CORE_MODE_INDEX) => Self::Mode,
+            (KVM_REG_RISCV_CSR, _) => Self::Csr(index),
+            (KVM_REG_RISCV_TIMER, _) => Self::Timer(index),
+            (KVM_REG_RISCV_SBI_EXT, _)
+                if id & KVM_REG_RISCV_SUBTYPE_MASK == KVM_REG_RISCV_SBI_SINGLE =>
+            {
+                Self::SbiExt(index)
+            }
+            _ => return_errno_with_message!(Errno::EINVAL, "the register is not supported"),
+        };
+        Ok(reg)
+    }
+}
+
+/// Reads a general register, where the registers are indexed as in `struct user_regs_struct`
+/// (i.e., `x1`-`x31` are indexed by 1-31).
+fn read_general_reg(regs: &GeneralRegs, index: usize) -> usize {
+    let offset = index * size_of::<usize>();
+    usize::from_bytes(&regs.as_bytes()[offset..offset + size_of::<usize>()])
+}
+
+fn write_general_reg(regs: &mut GeneralRegs, index: usize, value: usize) {
+    let offset = index * size_of::<usize>();
+    regs.as_bytes_mut()[offset..offset + size_of::<usize>()].copy_from_slice(value.as_bytes());
+}
+
+impl Pollable for Vcpu {
+    fn poll(&self, mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        let events = IoEvents::IN | IoEvents::OUT;
+        events & mask
+    }
+}
+
+impl FileLike for Vcpu {
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        match cmd {
+            IoctlCmd::KVM_RUN => self.run(),
+            IoctlCmd::KVM_INTERRUPT => {
+                let irq: u32 = current_userspace!().read_val(arg)?;
+                self.set_external_irq(irq)?;
+                Ok(0)
+            }
+            IoctlCmd::KVM_GET_MP_STATE => {
+                let mp_state = match *self.hart_state.lock() {
+                    HartState::Stopped => KVM_MP_STATE_STOPPED,
+                    _ => KVM_MP_STATE_RUNNABLE,
+                };
+                current_userspace!().write_val(arg, &mp_state)?;
+                Ok(0)
+            }
+            IoctlCmd::KVM_SET_MP_STATE => {
+                let mp_state: u32 = current_userspace!().read_val(arg)?;
+                let hart_state = match mp_state {
+                    KVM_MP_STATE_RUNNABLE => HartState::Started,
+                    KVM_MP_STATE_STOPPED => HartState::Stopped,
+                    _ => return_errno_with_message!(Errno::EINVAL, "the MP state is invalid"),
+                };
+                *self.hart_state.lock() = hart_state;
+                self.wfi_queue.wake_all();
+                Ok(0)
+            }
+            IoctlCmd::KVM_GET_ONE_REG => {
+                let user_space = current_userspace!();
+                let one_reg: CKvmOneReg = user_space.read_val(arg)?;
+                let value = self.get_reg(VcpuReg::parse(one_reg.id)?)?;
+                user_space.write_val(one_reg.addr as Vaddr, &(value as u64))?;
+                Ok(0)
+            }
+            IoctlCmd::KVM_SET_ONE_REG => {
+                let user_space = current_userspace!();
+                let one_reg: CKvmOneReg = user_space.read_val(arg)?;
+                let value: u64 = user_space.read_val(one_reg.addr as Vaddr)?;
+                self.set_reg(VcpuReg::parse(one_reg.id)?, value as usize)?;
+                Ok(0)
+            }
+            _ => return_errno_with_message!(Errno::ENOTTY, "the ioctl command is unknown"),
+        }
+    }
+
+    fn metadata(&self) -> Metadata {
+        anon_metadata()
+    }
+}
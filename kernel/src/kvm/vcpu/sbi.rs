@@ -0,0 +1,453 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The SBI implementation for the guests.
+//!
+//! The SBI calls of a guest exit from the guest as the `ecall`s of the VS mode. The extensions
+//! that only need the states of the vCPUs are emulated in the kernel:
+//!  - the base extension, with which the guest probes the other extensions;
+//!  - the TIME extension, with the virtual timer of the vCPU;
+//!  - the IPI extension, by injecting the software interrupts into the target vCPUs;
+//!  - the RFENCE extension, by synchronizing the instruction cache or flushing the VS-stage
+//!    TLB entries before the target vCPUs run again;
+//!  - the HSM extension, for starting and stopping the vCPUs, where only the retentive
+//!    suspension is supported;
+//!  - the SRST extension and the legacy timer and shutdown calls, which exit to the user space
+//!    as the system events.
+//!
+//! The calls of the other known extensions (e.g., the debug console) are forwarded to the user
+//! space with `KVM_EXIT_RISCV_SBI`. The user space can enable or disable each extension by
+//! `KVM_SET_ONE_REG` before the guest runs, and the disabled extensions are unavailable to the
+//! guest. Like Linux, the extensions that need the help of the user space, except the
+//! experimental and vendor ones, are disabled by default.
+//!
+//! Reference: <https://github.com/riscv-non-isa/riscv-sbi-doc>
+
+use core::sync::atomic::Ordering;
+
+use ostd::arch::virt::GuestContext;
+
+use super::{
+    CKvmRunSbi, PendingExit, Vcpu, VcpuState, EXIT_INFO_OFFSET, KVM_EXIT_RISCV_SBI,
+    KVM_EXIT_SYSTEM_EVENT, SIP_SSIP, SSTATUS_SIE,
+};
+use crate::prelude::*;
+
+// The SBI extensions.
+const SBI_EXT_0_1_SET_TIMER: usize = 0x00;
+const SBI_EXT_0_1_SHUTDOWN: usize = 0x08;
+const SBI_EXT_0_1_LAST: usize = 0x0f;
+const SBI_EXT_BASE: usize = 0x10;
+const SBI_EXT_TIME: usize = 0x5449_4D45;
+const SBI_EXT_IPI: usize = 0x73_5049;
+const SBI_EXT_RFENCE: usize = 0x5246_4E43;
+const SBI_EXT_HSM: usize = 0x48_534D;
+const SBI_EXT_SRST: usize = 0x5352_5354;
+const SBI_EXT_PMU: usize = 0x50_4D55;
+const SBI_EXT_DBCN: usize = 0x4442_434E;
+const SBI_EXT_EXPERIMENTAL_START: usize = 0x0800_0000;
+const SBI_EXT_EXPERIMENTAL_END: usize = 0x08FF_FFFF;
+const SBI_EXT_VENDOR_START: usize = 0x0900_0000;
+const SBI_EXT_VENDOR_END: usize = 0x09FF_FFFF;
+
+// The functions of the extensions.
+const SBI_BASE_GET_SPEC_VERSION: usize = 0;
+const SBI_BASE_PROBE_EXTENSION: usize = 3;
+const SBI_TIME_SET_TIMER: usize = 0;
+const SBI_IPI_SEND_IPI: usize = 0;
+const SBI_RFENCE_REMOTE_FENCE_I: usize = 0;
+const SBI_RFENCE_REMOTE_SFENCE_VMA: usize = 1;
+const SBI_RFENCE_REMOTE_SFENCE_VMA_ASID: usize = 2;
+const SBI_HSM_HART_START: usize = 0;
+const SBI_HSM_HART_STOP: usize = 1;
+const SBI_HSM_HART_GET_STATUS: usize = 2;
+const SBI_HSM_HART_SUSPEND: usize = 3;
+const SBI_SRST_SYSTEM_RESET: usize = 0;
+
+/// The version of the SBI specification, i.e., v1.0.
+const SBI_SPEC_VERSION: usize = 1 << 24;
+
+const SBI_SUCCESS: isize = 0;
+const SBI_ERR_NOT_SUPPORTED: isize = -2;
+const SBI_ERR_INVALID_PARAM: isize = -3;
+const SBI_ERR_ALREADY_AVAILABLE: isize = -6;
+
+/// The hart mask base that selects all the harts.
+const HART_MASK_BASE_ALL: usize = usize::MAX;
+
+// The states returned by `sbi_hart_get_status`.
+const SBI_HSM_STATE_STARTED: usize = 0;
+const SBI_HSM_STATE_STOPPED: usize = 1;
+const SBI_HSM_STATE_START_PENDING: usize = 2;
+
+const SBI_HSM_SUSPEND_RETENTIVE: usize = 0;
+
+const SBI_SRST_RESET_TYPE_SHUTDOWN: usize = 0;
+const SBI_SRST_RESET_TYPE_COLD_REBOOT: usize = 1;
+const SBI_SRST_RESET_TYPE_WARM_REBOOT: usize = 2;
+
+const KVM_SYSTEM_EVENT_SHUTDOWN: u32 = 1;
+const KVM_SYSTEM_EVENT_RESET: u32 = 2;
+
+/// The `system_event` member of the union in `struct kvm_run`, without the data.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct CKvmRunSystemEvent {
+    type_: u32,
+    ndata: u32,
+}
+
+/// An SBI extension that can be enabled or disabled by the user space.
+///
+/// The values are those of `enum kvm_riscv_sbi_ext_id` in Linux.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub(super) enum SbiExt {
+    V01 = 0,
+    Time = 1,
+    Ipi = 2,
+    Rfence = 3,
+    Srst = 4,
+    Hsm = 5,
+    Pmu = 6,
+    Experimental = 7,
+    Vendor = 8,
+    Dbcn = 9,
+}
+
+/// How the calls of an SBI extension are handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SbiPolicy {
+    /// The calls are emulated in the kernel.
+    Emulate,
+    /// The calls are forwarded to the user space.
+    Forward,
+}
+
+impl SbiExt {
+    /// All the extensions, in the order of the values.
+    const ALL: [Self; 10] = [
+        Self::V01,
+        Self::Time,
+        Self::Ipi,
+        Self::Rfence,
+        Self::Srst,
+        Self::Hsm,
+        Self::Pmu,
+        Self::Experimental,
+        Self::Vendor,
+        Self::Dbcn,
+    ];
+
+    /// Returns the extension of the value in `enum kvm_riscv_sbi_ext_id`.
+    pub(super) fn from_kvm_id(id: usize) -> Option<Self> {
+        Self::ALL.get(id).copied()
+    }
+
+    /// Returns the extension of the SBI extension ID.
+    ///
+    /// It returns `None` for the base extension, which is always available.
+    fn from_eid(eid: usize) -> Option<Self> {
+        let ext = match eid {
+            0..=SBI_EXT_0_1_LAST => Self::V01,
+            SBI_EXT_TIME => Self::Time,
+            SBI_EXT_IPI => Self::Ipi,
+            SBI_EXT_RFENCE => Self::Rfence,
+            SBI_EXT_SRST => Self::Srst,
+            SBI_EXT_HSM => Self::Hsm,
+            SBI_EXT_PMU => Self::Pmu,
+            SBI_EXT_EXPERIMENTAL_START..=SBI_EXT_EXPERIMENTAL_END => Self::Experimental,
+            SBI_EXT_VENDOR_START..=SBI_EXT_VENDOR_END => Self::Vendor,
+            SBI_EXT_DBCN => Self::Dbcn,
+            _ => return None,
+        };
+        Some(ext)
+    }
+
+    fn policy(self) -> SbiPolicy {
+        match self {
+            Self::V01 | Self::Time | Self::Ipi | Self::Rfence | Self::Srst | Self::Hsm => {
+                SbiPolicy::Emulate
+            }
+            Self::Pmu | Self::Experimental | Self::Vendor | Self::Dbcn => SbiPolicy::Forward,
+        }
+    }
+
+    fn is_enabled_by_default(self) -> bool {
+        !matches!(self, Self::Pmu | Self::Dbcn)
+    }
+}
+
+/// The SBI extensions that are enabled for a vCPU.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct SbiExts(u32);
+
+impl SbiExts {
+    pub(super) fn new() -> Self {
+        let bits = SbiExt::ALL
+            .iter()
+            .filter(|ext| ext.is_enabled_by_default())
+            .fold(0, |bits, ext| bits | (1 << *ext as u32));
+        Self(bits)
+    }
+
+    pub(super) fn is_enabled(&self, ext: SbiExt) -> bool {
+        self.0 & (1 << ext as u32) != 0
+    }
+
+    pub(super) fn set_enabled(&mut self, ext: SbiExt, is_enabled: bool) {
+        if is_enabled {
+            self.0 |= 1 << ext as u32;
+        } else {
+            self.0 &= !(1 << ext as u32);
+        }
+    }
+}
+
+/// The state of a vCPU in the HSM extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum HartState {
+    Started,
+    Stopped,
+    /// The vCPU is started by another vCPU, and starts at the address when it runs again.
+    StartPending {
+        start_addr: usize,
+        opaque: usize,
+    },
+}
+
+bitflags! {
+    /// The fences that are requested by the RFENCE extension.
+    pub(super) struct RemoteFences: u8 {
+        const FENCE_I    = 1 << 0;
+        const SFENCE_VMA = 1 << 1;
+    }
+}
+
+/// The result of an SBI call that is emulated in the kernel.
+enum SbiReturn {
+    /// Returns the error and the value to the guest.
+    Value(isize, usize),
+    /// Forwards the call to the user space.
+    Forward,
+    /// Exits to the user space as a system event.
+    SystemEvent(u32),
+    /// Returns nothing to the guest, e.g., after the vCPU is stopped.
+    None,
+}
+
+impl Vcpu {
+    /// Handles an SBI call, and returns the exit reason if the user space should handle it.
+    pub(super) fn handle_sbi_call(&self, state: &mut VcpuState) -> Result<Option<u32>> {
+        let regs = state.ctx.general_regs();
+        let (eid, fid) = (regs.a7, regs.a6);
+        let args = [regs.a0, regs.a1, regs.a2, regs.a3, regs.a4, regs.a5];
+
+        let policy = match SbiExt::from_eid(eid) {
+            None if eid == SBI_EXT_BASE => Some(SbiPolicy::Emulate),
+            Some(ext) if state.sbi_exts.is_enabled(ext) => Some(ext.policy()),
+            _ => None,
+        };
+        let result = match policy {
+            Some(SbiPolicy::Emulate) => self.emulate_sbi_call(state, eid, fid, &args),
+            Some(SbiPolicy::Forward) => SbiReturn::Forward,
+            None => SbiReturn::Value(SBI_ERR_NOT_SUPPORTED, 0),
+        };
+
+        let (error, value) = match result {
+            SbiReturn::Value(error, value) => (error, value),
+            SbiReturn::Forward => {
+                let info = CKvmRunSbi {
+                    extension_id: eid as u64,
+                    function_id: fid as u64,
+                    args: args.map(|arg| arg as u64),
+                    ret: [SBI_ERR_NOT_SUPPORTED as u64, 0],
+                };
+                self.run.write_val(EXIT_INFO_OFFSET, &info)?;
+                state.pending = Some(PendingExit::Sbi);
+                return Ok(Some(KVM_EXIT_RISCV_SBI));
+            }
+            SbiReturn::SystemEvent(type_) => {
+                let info = CKvmRunSystemEvent { type_, ndata: 0 };
+                self.run.write_val(EXIT_INFO_OFFSET, &info)?;
+                return Ok(Some(KVM_EXIT_SYSTEM_EVENT));
+            }
+            SbiReturn::None => return Ok(None),
+        };
+
+        let regs = state.ctx.general_regs_mut();
+        regs.a0 = error as usize;
+        // The legacy extensions return only in `a0`.
+        if eid > SBI_EXT_0_1_LAST {
+            regs.a1 = value;
+        }
+        Ok(None)
+    }
+
+    fn emulate_sbi_call(
+        &self,
+        state: &mut VcpuState,
+        eid: usize,
+        fid: usize,
+        args: &[usize; 6],
+    ) -> SbiReturn {
+        match (eid, fid) {
+            (SBI_EXT_0_1_SET_TIMER, _) | (SBI_EXT_TIME, SBI_TIME_SET_TIMER) => {
+                state.timer_compare = args[0] as u64;
+                state.is_timer_on = true;
+                SbiReturn::Value(SBI_SUCCESS, 0)
+            }
+            (SBI_EXT_0_1_SHUTDOWN, _) => SbiReturn::SystemEvent(KVM_SYSTEM_EVENT_SHUTDOWN),
+            // The other legacy calls (e.g., the console) need the help of the user space.
+            (0..=SBI_EXT_0_1_LAST, _) => SbiReturn::Forward,
+
+            (SBI_EXT_BASE, SBI_BASE_GET_SPEC_VERSION) => {
+                SbiReturn::Value(SBI_SUCCESS, SBI_SPEC_VERSION)
+            }
+            (SBI_EXT_BASE, SBI_BASE_PROBE_EXTENSION) => {
+                let is_available = args[0] == SBI_EXT_BASE
+                    || SbiExt::from_eid(args[0]).is_some_and(|ext| state.sbi_exts.is_enabled(ext));
+                SbiReturn::Value(SBI_SUCCESS, is_available as usize)
+            }
+            // The implementation ID, the implementation version, and the machine IDs.
+            (SBI_EXT_BASE, 1..=2 | 4..=6) => SbiReturn::Value(SBI_SUCCESS, 0),
+
+            (SBI_EXT_IPI, SBI_IPI_SEND_IPI) => self.for_each_hart(args[0], args[1], |vcpu| {
+                vcpu.injected_irqs.fetch_or(SIP_SSIP, Ordering::Relaxed);
+                vcpu.wfi_queue.wake_all();
+            }),
+
+            // The address ranges and the ASIDs are ignored, and all the entries are flushed.
+            (SBI_EXT_RFENCE, SBI_RFENCE_REMOTE_FENCE_I) => {
+                self.for_each_hart(args[0], args[1], |vcpu| {
+                    vcpu.remote_fences
+                        .fetch_or(RemoteFences::FENCE_I.bits(), Ordering::Relaxed);
+                })
+            }
+            (SBI_EXT_RFENCE, SBI_RFENCE_REMOTE_SFENCE_VMA | SBI_RFENCE_REMOTE_SFENCE_VMA_ASID) => {
+                self.for_each_hart(args[0], args[1], |vcpu| {
+                    vcpu.remote_fences
+                        .fetch_or(RemoteFences::SFENCE_VMA.bits(), Ordering::Relaxed);
+                })
+            }
+
+            (SBI_EXT_HSM, SBI_HSM_HART_START) => self.hart_start(args[0], args[1], args[2]),
+            (SBI_EXT_HSM, SBI_HSM_HART_STOP) => {
+                *self.hart_state.lock() = HartState::Stopped;
+                SbiReturn::None
+            }
+            (SBI_EXT_HSM, SBI_HSM_HART_GET_STATUS) => self.hart_get_status(args[0]),
+            (SBI_EXT_HSM, SBI_HSM_HART_SUSPEND) if args[0] == SBI_HSM_SUSPEND_RETENTIVE => {
+                self.wait_for_interrupt(state);
+                SbiReturn::Value(SBI_SUCCESS, 0)
+            }
+
+            (SBI_EXT_SRST, SBI_SRST_SYSTEM_RESET) => match args[0] {
+                SBI_SRST_RESET_TYPE_SHUTDOWN => SbiReturn::SystemEvent(KVM_SYSTEM_EVENT_SHUTDOWN),
+                SBI_SRST_RESET_TYPE_COLD_REBOOT | SBI_SRST_RESET_TYPE_WARM_REBOOT => {
+                    SbiReturn::SystemEvent(KVM_SYSTEM_EVENT_RESET)
+                }
+                _ => SbiReturn::Value(SBI_ERR_INVALID_PARAM, 0),
+            },
+
+            _ => SbiReturn::Value(SBI_ERR_NOT_SUPPORTED, 0),
+        }
+    }
+
+    /// Calls the closure for each vCPU in the hart mask.
+    ///
+    /// It fails with `SBI_ERR_INVALID_PARAM` if a hart in the mask does not exist.
+    fn for_each_hart(
+        &self,
+        hart_mask: usize,
+        hart_mask_base: usize,
+        mut f: impl FnMut(&Vcpu),
+    ) -> SbiReturn {
+        let vcpus = if hart_mask_base == HART_MASK_BASE_ALL {
+            Some(self.vm.vcpus())
+        } else {
+            (0..usize::BITS as usize)
+                .filter(|bit| hart_mask & (1 << bit) != 0)
+                .map(|bit| {
+                    let id = hart_mask_base.checked_add(bit)?;
+                    self.vm.vcpu(u32::try_from(id).ok()?)
+                })
+                .collect::<Option<Vec<_>>>()
+        };
+        let Some(vcpus) = vcpus else {
+            return SbiReturn::Value(SBI_ERR_INVALID_PARAM, 0);
+        };
+
+        for vcpu in vcpus.iter() {
+            f(vcpu);
+        }
+        SbiReturn::Value(SBI_SUCCESS, 0)
+    }
+
+    fn hart_start(&self, hartid: usize, start_addr: usize, opaque: usize) -> SbiReturn {
+        let Some(vcpu) = u32::try_from(hartid).ok().and_then(|id| self.vm.vcpu(id)) else {
+            return SbiReturn::Value(SBI_ERR_INVALID_PARAM, 0);
+        };
+
+        let mut hart_state = vcpu.hart_state.lock();
+        if *hart_state != HartState::Stopped {
+            return SbiReturn::Value(SBI_ERR_ALREADY_AVAILABLE, 0);
+        }
+        *hart_state = HartState::StartPending { start_addr, opaque };
+        drop(hart_state);
+
+        vcpu.wfi_queue.wake_all();
+        SbiReturn::Value(SBI_SUCCESS, 0)
+    }
+
+    fn hart_get_status(&self, hartid: usize) -> SbiReturn {
+        let Some(vcpu) = u32::try_from(hartid).ok().and_then(|id| self.vm.vcpu(id)) else {
+            return SbiReturn::Value(SBI_ERR_INVALID_PARAM, 0);
+        };
+
+        let status = match *vcpu.hart_state.lock() {
+            HartState::Started => SBI_HSM_STATE_STARTED,
+            HartState::Stopped => SBI_HSM_STATE_STOPPED,
+            HartState::StartPending { .. } => SBI_HSM_STATE_START_PENDING,
+        };
+        SbiReturn::Value(SBI_SUCCESS, status)
+    }
+
+    /// Waits until the vCPU is started if it is stopped.
+    ///
+    /// If the vCPU is started by `sbi_hart_start`, the vCPU is reset to start at the requested
+    /// address, with its hart ID in `a0` and the opaque value in `a1`.
+    pub(super) fn wait_until_started(&self, state: &mut VcpuState) -> Result<()> {
+        let hart_state = self.wfi_queue.pause_until(|| {
+            let hart_state = *self.hart_state.lock();
+            (hart_state != HartState::Stopped).then_some(hart_state)
+        })?;
+
+        let HartState::StartPending { start_addr, opaque } = hart_state else {
+            return Ok(());
+        };
+        let ctx = &mut state.ctx;
+        ctx.set_pc(start_addr);
+        let regs = ctx.general_regs_mut();
+        regs.a0 = self.id as usize;
+        regs.a1 = opaque;
+        let csrs = ctx.vs_csrs_mut();
+        csrs.vsstatus &= !SSTATUS_SIE;
+        csrs.vsatp = 0;
+
+        *self.hart_state.lock() = HartState::Started;
+        Ok(())
+    }
+
+    /// Takes the fences that are requested by the other vCPUs, which are done before the guest
+    /// runs again.
+    pub(super) fn take_remote_fences(&self, ctx: &mut GuestContext) {
+        let fences =
+            RemoteFences::from_bits_truncate(self.remote_fences.swap(0, Ordering::Relaxed));
+        if fences.contains(RemoteFences::FENCE_I) {
+            ctx.request_icache_sync();
+        }
+        if fences.contains(RemoteFences::SFENCE_VMA) {
+            ctx.request_tlb_flush();
+        }
+    }
+}
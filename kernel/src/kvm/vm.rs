@@ -0,0 +1,325 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::ops::Range;
+
+use ostd::{
+    arch::virt::{nr_guest_files, GuestPageTable, GuestPerms},
+    mm::vm_space::VmItem,
+};
+
+use super::{
+    aia::{AiaDevice, AiaState, KVM_DEV_TYPE_RISCV_AIA},
+    anon_metadata, insert_file,
+    vcpu::Vcpu,
+    KVM_MAX_VCPUS,
+};
+use crate::{
+    current_userspace,
+    events::IoEvents,
+    fs::{
+        file_handle::FileLike,
+        utils::{IoctlCmd, Metadata},
+    },
+    prelude::*,
+    process::signal::{PollHandle, Pollable},
+    thread::exception::PageFaultInfo,
+    vm::perms::VmPerms,
+};
+
+/// The maximum number of the memory regions in a VM.
+pub(super) const KVM_USER_MEM_SLOTS: u32 = 32;
+
+/// `struct kvm_userspace_memory_region` in Linux.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct CUserMemoryRegion {
+    slot: u32,
+    flags: u32,
+    guest_phys_addr: u64,
+    memory_size: u64,
+    userspace_addr: u64,
+}
+
+bitflags! {
+    /// The flags of `struct kvm_userspace_memory_region`.
+    struct MemoryRegionFlags: u32 {
+        const LOG_DIRTY_PAGES = 1 << 0;
+        const READONLY        = 1 << 1;
+    }
+}
+
+/// `struct kvm_msi` in Linux.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct CKvmMsi {
+    address_lo: u32,
+    address_hi: u32,
+    data: u32,
+    flags: u32,
+    devid: u32,
+    pad: [u8; 12],
+}
+
+/// `struct kvm_create_device` in Linux.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct CKvmCreateDevice {
+    type_: u32,
+    fd: u32,
+    flags: u32,
+}
+
+/// The flag of `struct kvm_create_device` that only tests whether the device can be created.
+const KVM_CREATE_DEVICE_TEST: u32 = 1 << 0;
+
+/// A memory region, which maps some user pages as the guest memory.
+#[derive(Debug, Clone, Copy)]
+struct MemoryRegion {
+    gpa: usize,
+    size: usize,
+}
+
+/// A VM, which owns the guest memory and the vCPUs.
+pub(super) struct Vm {
+    page_table: Arc<GuestPageTable>,
+    regions: Mutex<BTreeMap<u32, MemoryRegion>>,
+    vcpus: Mutex<BTreeMap<u32, Weak<Vcpu>>>,
+    /// The configuration of the AIA device, which is `None` if the device is not created.
+    aia: Mutex<Option<AiaState>>,
+    weak_self: Weak<Vm>,
+}
+
+impl Vm {
+    pub(super) fn new() -> Result<Arc<Self>> {
+        let page_table = Arc::new(GuestPageTable::new()?);
+        Ok(Arc::new_cyclic(|weak_self| Self {
+            page_table,
+            regions: Mutex::new(BTreeMap::new()),
+            vcpus: Mutex::new(BTreeMap::new()),
+            aia: Mutex::new(None),
+            weak_self: weak_self.clone(),
+        }))
+    }
+
+    /// Sets, replaces or deletes a memory region.
+    ///
+    /// The user pages are committed and pinned, so that the guest memory is backed by the same
+    /// frames as the user memory.
+    fn set_memory_region(&self, region: &CUserMemoryRegion) -> Result<()> {
+        let Some(flags) = MemoryRegionFlags::from_bits(region.flags) else {
+            return_errno_with_message!(Errno::EINVAL, "the memory region flags are invalid");
+        };
+        if flags.contains(MemoryRegionFlags::LOG_DIRTY_PAGES) {
+            return_errno_with_message!(Errno::EINVAL, "dirty page logging is not supported");
+        }
+        if region.slot >= KVM_USER_MEM_SLOTS {
+            return_errno_with_message!(Errno::EINVAL, "the memory slot is invalid");
+        }
+
+        let gpa = region.guest_phys_addr as usize;
+        let size = region.memory_size as usize;
+        let uaddr = region.userspace_addr as Vaddr;
+        if gpa % PAGE_SIZE != 0 || size % PAGE_SIZE != 0 || uaddr % PAGE_SIZE != 0 {
+            return_errno_with_message!(Errno::EINVAL, "the memory region is not page-aligned");
+        }
+        if gpa.checked_add(size).is_none() || uaddr.checked_add(size).is_none() {
+            return_errno_with_message!(Errno::EINVAL, "the memory region overflows");
+        }
+
+        let mut regions = self.regions.lock();
+        let new_range = gpa..gpa + size;
+        if regions.iter().any(|(slot, old)| {
+            *slot != region.slot && new_range.start < old.gpa + old.size && old.gpa < new_range.end
+        }) {
+            return_errno_with_message!(Errno::EEXIST, "the memory region overlaps another one");
+        }
+
+        if let Some(old) = regions.remove(&region.slot) {
+            self.unmap_pages(old.gpa..old.gpa + old.size);
+        }
+        if size == 0 {
+            return Ok(());
+        }
+
+        let (vm_perms, guest_perms) = if flags.contains(MemoryRegionFlags::READONLY) {
+            (VmPerms::READ, GuestPerms::READ | GuestPerms::EXEC)
+        } else {
+            (
+                VmPerms::READ | VmPerms::WRITE,
+                GuestPerms::READ | GuestPerms::WRITE | GuestPerms::EXEC,
+            )
+        };
+        if let Err(err) = self.map_pages(uaddr, new_range.clone(), vm_perms, guest_perms) {
+            self.unmap_pages(new_range);
+            return Err(err);
+        }
+        regions.insert(region.slot, MemoryRegion { gpa, size });
+
+        Ok(())
+    }
+
+    fn map_pages(
+        &self,
+        uaddr: Vaddr,
+        gpa_range: Range<usize>,
+        vm_perms: VmPerms,
+        guest_perms: GuestPerms,
+    ) -> Result<()> {
+        let user_space = current_userspace!();
+        let root_vmar = user_space.root_vmar();
+        let vm_space = root_vmar.vm_space();
+
+        for gpa in gpa_range.clone().step_by(PAGE_SIZE) {
+            let vaddr = uaddr + (gpa - gpa_range.start);
+            // The page fault commits the page, and breaks the copy-on-write sharing if the
+            // guest can write to the page.
+            root_vmar.handle_page_fault(&PageFaultInfo {
+                address: vaddr,
+                required_perms: vm_perms,
+            })?;
+
+            let mut cursor = vm_space.cursor(&(vaddr..vaddr + PAGE_SIZE))?;
+            let VmItem::Mapped { frame, .. } = cursor.query()? else {
+                return_errno_with_message!(Errno::EFAULT, "the user page is not mapped");
+            };
+            drop(cursor);
+            self.page_table.map(gpa, frame, guest_perms)?;
+        }
+
+        Ok(())
+    }
+
+    fn unmap_pages(&self, gpa_range: Range<usize>) {
+        for gpa in gpa_range.step_by(PAGE_SIZE) {
+            let _ = self.page_table.unmap(gpa);
+        }
+    }
+
+    fn create_vcpu(&self, id: u32) -> Result<i32> {
+        if id >= KVM_MAX_VCPUS {
+            return_errno_with_message!(Errno::EINVAL, "the vCPU ID is invalid");
+        }
+        let mut vcpus = self.vcpus.lock();
+        if vcpus.contains_key(&id) {
+            return_errno_with_message!(Errno::EEXIST, "the vCPU already exists");
+        }
+
+        let vcpu = Vcpu::new(self.weak_self.upgrade().unwrap(), id)?;
+        let fd = insert_file(vcpu.clone())?;
+        vcpus.insert(id, Arc::downgrade(&vcpu));
+        Ok(fd)
+    }
+
+    /// Creates the AIA device, which is the only device that can be created.
+    fn create_device(&self, arg: Vaddr) -> Result<i32> {
+        let user_space = current_userspace!();
+        let mut create_device: CKvmCreateDevice = user_space.read_val(arg)?;
+
+        if create_device.type_ != KVM_DEV_TYPE_RISCV_AIA || nr_guest_files() == 0 {
+            return_errno_with_message!(Errno::ENODEV, "the device type is not supported");
+        }
+        if create_device.flags & !KVM_CREATE_DEVICE_TEST != 0 {
+            return_errno_with_message!(Errno::EINVAL, "the device flags are invalid");
+        }
+        if create_device.flags & KVM_CREATE_DEVICE_TEST != 0 {
+            return Ok(0);
+        }
+
+        let mut aia = self.aia.lock();
+        if aia.is_some() {
+            return_errno_with_message!(Errno::EEXIST, "the AIA device already exists");
+        }
+        let device = AiaDevice::new(self.weak_self.upgrade().unwrap());
+        create_device.fd = insert_file(Arc::new(device))? as u32;
+        *aia = Some(AiaState::new());
+        drop(aia);
+
+        user_space.write_val(arg, &create_device)?;
+        Ok(0)
+    }
+
+    /// Sends an MSI to the vCPU whose IMSIC is at the address of the MSI.
+    fn signal_msi(&self, msi: &CKvmMsi) -> Result<i32> {
+        if msi.flags != 0 {
+            return_errno_with_message!(Errno::EINVAL, "the MSI flags are invalid");
+        }
+
+        let gpa = ((msi.address_hi as usize) << 32) | msi.address_lo as usize;
+        let vcpu = self
+            .aia
+            .lock()
+            .as_ref()
+            .and_then(|aia| aia.imsic_vcpu(gpa))
+            .and_then(|id| self.vcpu(id));
+        let Some(vcpu) = vcpu else {
+            return_errno_with_message!(Errno::EINVAL, "no IMSIC is at the MSI address");
+        };
+
+        vcpu.send_msi(msi.data)?;
+        // The MSI is delivered.
+        Ok(1)
+    }
+
+    pub(super) fn page_table(&self) -> &Arc<GuestPageTable> {
+        &self.page_table
+    }
+
+    pub(super) fn aia(&self) -> &Mutex<Option<AiaState>> {
+        &self.aia
+    }
+
+    /// Returns the vCPU of the ID, if it exists.
+    pub(super) fn vcpu(&self, id: u32) -> Option<Arc<Vcpu>> {
+        self.vcpus.lock().get(&id).and_then(Weak::upgrade)
+    }
+
+    /// Returns the vCPUs that exist.
+    pub(super) fn vcpus(&self) -> Vec<Arc<Vcpu>> {
+        self.vcpus
+            .lock()
+            .values()
+            .filter_map(Weak::upgrade)
+            .collect()
+    }
+
+    /// Returns the IDs of the vCPUs that have been created.
+    pub(super) fn vcpu_ids(&self) -> Vec<u32> {
+        self.vcpus.lock().keys().copied().collect()
+    }
+
+    /// Returns the guest physical address of the IMSIC of the vCPU, if the guest has the
+    /// IMSICs.
+    pub(super) fn imsic_gpa(&self, vcpu_id: u32) -> Option<usize> {
+        self.aia.lock().as_ref()?.imsic_gpa(vcpu_id)
+    }
+}
+
+impl Pollable for Vm {
+    fn poll(&self, mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        let events = IoEvents::IN | IoEvents::OUT;
+        events & mask
+    }
+}
+
+impl FileLike for Vm {
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        match cmd {
+            IoctlCmd::KVM_SET_USER_MEMORY_REGION => {
+                let region: CUserMemoryRegion = current_userspace!().read_val(arg)?;
+                self.set_memory_region(&region)?;
+                Ok(0)
+            }
+            IoctlCmd::KVM_CREATE_VCPU => self.create_vcpu(arg as u32),
+            IoctlCmd::KVM_CREATE_DEVICE => self.create_device(arg),
+            IoctlCmd::KVM_SIGNAL_MSI => {
+                let msi: CKvmMsi = current_userspace!().read_val(arg)?;
+                self.signal_msi(&msi)
+            }
+            _ => return_errno_with_message!(Errno::ENOTTY, "the ioctl command is unknown"),
+        }
+    }
+
+    fn metadata(&self) -> Metadata {
+        anon_metadata()
+    }
+}
@@ -42,6 +42,8 @@ use sched::SchedPolicy;
 use crate::{prelude::*, thread::kernel_thread::ThreadOptions};
 
 extern crate alloc;
+// The SPI flashes are only used through `aster-mtd`, so the driver must be linked explicitly.
+extern crate aster_spi;
 extern crate lru;
 #[macro_use]
 extern crate controlled;
@@ -49,8 +51,10 @@ extern crate controlled;
 extern crate getset;
 
 pub mod arch;
+mod bpf;
 pub mod context;
 pub mod cpu;
+mod crypto;
 pub mod device;
 pub mod driver;
 pub mod error;
@@ -58,13 +62,23 @@ pub mod events;
 pub mod fs;
 pub mod ipc;
 pub mod kcmdline;
+#[cfg(target_arch = "riscv64")]
+mod kexec;
+#[cfg(target_arch = "riscv64")]
+mod kvm;
 pub mod net;
+mod perf;
+mod power;
 pub mod prelude;
 mod process;
+#[cfg(target_arch = "riscv64")]
+mod pstore;
 mod sched;
+pub mod security;
 pub mod syscall;
 pub mod thread;
 pub mod time;
+mod trace;
 mod util;
 pub(crate) mod vdso;
 pub mod vm;
@@ -90,10 +104,14 @@ pub fn main() {
 
 pub fn init() {
     thread::init();
+    trace::init();
+    crypto::init();
+    security::init();
     util::random::init();
     driver::init();
     time::init();
-    #[cfg(target_arch = "x86_64")]
+    #[cfg(target_arch = "riscv64")]
+    pstore::init();
     net::init();
     sched::init();
     fs::rootfs::init(boot_info().initramfs.expect("No initramfs found!")).unwrap();
@@ -101,6 +119,7 @@ pub fn init() {
     syscall::init();
     vdso::init();
     process::init();
+    thread::lockup::init_on_cpu();
 }
 
 fn ap_init() {
@@ -123,6 +142,8 @@ fn ap_init() {
         .cpu_affinity(cpu_id.into())
         .sched_policy(SchedPolicy::Idle)
         .spawn();
+
+    thread::lockup::init_on_cpu();
 }
 
 fn init_thread() {
@@ -130,9 +151,16 @@ fn init_thread() {
     // Work queue should be initialized before interrupt is enabled,
     // in case any irq handler uses work queue as bottom half
     thread::work_queue::init();
-    #[cfg(target_arch = "x86_64")]
+    let karg: KCmdlineArg = boot_info().kernel_cmdline.as_str().into();
+    vm::hugetlb::init(&karg);
     net::lazy_init();
+    // The network must be configured before the root file system is mounted, which may be
+    // on a network server.
+    net::ipconfig::init(karg.get_ip_config());
     fs::lazy_init();
+    if karg.get_root() == Some("/dev/nfs") {
+        fs::nfs::mount_root(karg.get_nfs_root());
+    }
     ipc::init();
     // driver::pci::virtio::block::block_device_test();
     let thread = ThreadOptions::new(|| {
@@ -143,8 +171,6 @@ fn init_thread() {
 
     print_banner();
 
-    let karg: KCmdlineArg = boot_info().kernel_cmdline.as_str().into();
-
     let initproc = Process::spawn_user_process(
         karg.get_initproc_path().unwrap(),
         karg.get_initproc_argv().to_vec(),
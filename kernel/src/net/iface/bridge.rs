@@ -0,0 +1,223 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Software bridges.
+//!
+//! A bridge connects its ports (i.e., TAP devices) to form a single Ethernet segment. The
+//! bridge learns the Ethernet addresses behind each port from the frames that it forwards, so
+//! frames destined for a known address are only sent to one port, while other frames are
+//! flooded to all the ports. The bridge itself is also an iface, which receives the frames
+//! destined for it and sends frames through the ports.
+//!
+//! The spanning tree protocol, VLAN filtering, and the aging of learned addresses are not
+//! supported.
+//!
+//! Reference: <https://docs.kernel.org/networking/bridge.html>.
+
+use alloc::collections::btree_map::BTreeMap;
+
+use aster_bigtcp::{
+    device::Medium,
+    iface::EtherIface,
+    wire::{EthernetAddress, EthernetFrame},
+};
+use ostd::sync::LocalIrqDisabled;
+
+use super::{
+    registry::{add_iface, remove_iface},
+    sched::PollScheduler,
+    tun::{random_ether_addr, OnTransmit, PacketQueue, QueueDevice, QueueDriver, TunIface},
+    Iface,
+};
+use crate::prelude::*;
+
+/// A software bridge.
+pub struct Bridge {
+    iface: Arc<Iface>,
+    /// The frames that will be received by the bridge iface
+    queue: Arc<PacketQueue>,
+    core: Arc<BridgeCore>,
+}
+
+/// All the bridges.
+static BRIDGES: Mutex<Vec<Arc<Bridge>>> = Mutex::new(Vec::new());
+
+impl Bridge {
+    /// Creates a bridge named `name`.
+    pub fn new(name: &str) -> Result<Arc<Self>> {
+        let ether_addr = random_ether_addr()?;
+        let queue = Arc::new(PacketQueue::new());
+        let core = Arc::new(BridgeCore {
+            ether_addr,
+            ports: SpinLock::new(Ports {
+                queues: Vec::new(),
+                fdb: BTreeMap::new(),
+                is_removed: false,
+            }),
+        });
+
+        let iface = add_iface(name, |name| {
+            let device = QueueDevice::with_on_transmit(
+                queue.clone(),
+                Medium::Ethernet,
+                BridgeForwarder(core.clone()),
+            );
+            let iface: Arc<Iface> = EtherIface::new(
+                QueueDriver::new(device),
+                ether_addr,
+                None,
+                None,
+                name,
+                PollScheduler::new(),
+            );
+            Ok(iface)
+        })?;
+
+        let bridge = Arc::new(Self { iface, queue, core });
+        BRIDGES.lock().push(bridge.clone());
+
+        Ok(bridge)
+    }
+
+    /// Finds the bridge whose iface index is `index`.
+    pub fn find(index: u32) -> Option<Arc<Self>> {
+        BRIDGES
+            .lock()
+            .iter()
+            .find(|bridge| bridge.iface.index() == index)
+            .cloned()
+    }
+
+    /// Removes the bridge.
+    ///
+    /// All the ports of the bridge are released from it.
+    pub fn remove(self: &Arc<Self>) {
+        BRIDGES.lock().retain(|bridge| !Arc::ptr_eq(bridge, self));
+
+        let queues = {
+            let mut ports = self.core.ports.lock();
+            ports.is_removed = true;
+            ports.fdb.clear();
+            core::mem::take(&mut ports.queues)
+        };
+        for (index, _) in queues {
+            if let Some(tun) = TunIface::find(index) {
+                tun.release_from(self);
+            }
+        }
+
+        remove_iface(&self.iface);
+    }
+
+    pub fn iface(&self) -> &Arc<Iface> {
+        &self.iface
+    }
+
+    /// Returns the iface indexes of the ports.
+    pub fn ports(&self) -> Vec<u32> {
+        self.core
+            .ports
+            .lock()
+            .queues
+            .iter()
+            .map(|(index, _)| *index)
+            .collect()
+    }
+
+    /// Adds a port whose frames are exchanged through `queue`.
+    pub(super) fn add_port(&self, index: u32, queue: Arc<PacketQueue>) -> Result<()> {
+        let mut ports = self.core.ports.lock();
+        if ports.is_removed {
+            return_errno_with_message!(Errno::ENODEV, "the bridge has been removed");
+        }
+        ports.queues.push((index, queue));
+
+        Ok(())
+    }
+
+    /// Removes a port and forgets the addresses learned from it.
+    pub(super) fn remove_port(&self, index: u32) {
+        let mut ports = self.core.ports.lock();
+        ports.queues.retain(|(other, _)| *other != index);
+        ports.fdb.retain(|_, port| *port != index);
+    }
+
+    /// Forwards a frame that is received from a port.
+    pub(super) fn forward_from_port(&self, index: u32, frame: &[u8]) {
+        if !self.core.forward(Some(index), frame) {
+            return;
+        }
+
+        self.queue.push_rx(frame.to_vec());
+        self.iface.poll();
+    }
+}
+
+struct BridgeCore {
+    ether_addr: EthernetAddress,
+    ports: SpinLock<Ports, LocalIrqDisabled>,
+}
+
+struct Ports {
+    /// The iface indexes of the ports and the queues through which their frames are exchanged
+    queues: Vec<(u32, Arc<PacketQueue>)>,
+    /// The forwarding database, which maps Ethernet addresses to the ports behind which they
+    /// are located
+    fdb: BTreeMap<[u8; 6], u32>,
+    /// Whether the bridge has been removed
+    is_removed: bool,
+}
+
+impl BridgeCore {
+    /// Forwards a frame from the port `src_port`, or from the bridge iface if `src_port` is
+    /// `None`.
+    ///
+    /// This method returns whether the frame should also be received by the bridge iface.
+    fn forward(&self, src_port: Option<u32>, frame: &[u8]) -> bool {
+        let Ok(parsed) = EthernetFrame::new_checked(frame) else {
+            return false;
+        };
+        let src_addr = parsed.src_addr();
+        let dst_addr = parsed.dst_addr();
+
+        let mut ports = self.ports.lock();
+        if ports.is_removed {
+            return false;
+        }
+
+        if let Some(src_port) = src_port {
+            if src_addr.is_unicast() {
+                ports.fdb.insert(src_addr.0, src_port);
+            }
+        }
+
+        if dst_addr == self.ether_addr {
+            return src_port.is_some();
+        }
+
+        let dst_port = if dst_addr.is_unicast() {
+            ports.fdb.get(&dst_addr.0).copied()
+        } else {
+            None
+        };
+        for (index, queue) in ports.queues.iter() {
+            if src_port == Some(*index) {
+                continue;
+            }
+            if dst_port.is_some_and(|dst_port| dst_port != *index) {
+                continue;
+            }
+            queue.push_tx(frame.to_vec());
+        }
+
+        src_port.is_some() && !dst_addr.is_unicast()
+    }
+}
+
+/// Forwards the frames sent by the bridge iface to the ports.
+struct BridgeForwarder(Arc<BridgeCore>);
+
+impl OnTransmit for BridgeForwarder {
+    fn on_transmit(&self, _queue: &PacketQueue, packet: Vec<u8>) {
+        self.0.forward(None, &packet);
+    }
+}
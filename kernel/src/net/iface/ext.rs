@@ -1,6 +1,6 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use super::sched::PollScheduler;
+use super::{filter::PacketFilter, sched::PollScheduler};
 use crate::net::socket::ip::{datagram::DatagramObserver, stream::StreamObserver};
 
 pub struct BigtcpExt;
@@ -10,4 +10,8 @@ impl aster_bigtcp::ext::Ext for BigtcpExt {
 
     type TcpEventObserver = StreamObserver;
     type UdpEventObserver = DatagramObserver;
+    type RawEventObserver = DatagramObserver;
+    type LinkEventObserver = DatagramObserver;
+
+    type PacketFilter = PacketFilter;
 }
@@ -0,0 +1,19 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use aster_bigtcp::filter::{FilterHook, FilterTable, PacketInfo, Verdict};
+use ostd::sync::LocalIrqDisabled;
+
+use crate::prelude::*;
+
+/// The rules that filter the packets of all ifaces.
+///
+/// The rules are configured by `NETLINK_NETFILTER` sockets.
+pub static FILTER_TABLE: RwLock<FilterTable, LocalIrqDisabled> = RwLock::new(FilterTable::new());
+
+pub struct PacketFilter;
+
+impl aster_bigtcp::filter::PacketFilter for PacketFilter {
+    fn filter(hook: FilterHook, packet: &PacketInfo) -> Verdict {
+        FILTER_TABLE.read().evaluate(hook, packet)
+    }
+}
@@ -3,33 +3,44 @@
 use alloc::{borrow::ToOwned, sync::Arc};
 
 use aster_bigtcp::device::WithDevice;
+use aster_network::AnyNetworkDevice;
 use ostd::sync::LocalIrqDisabled;
-use spin::Once;
 
-use super::{poll::poll_ifaces, Iface};
+use super::{
+    poll::poll_ifaces,
+    registry::{add_boot_iface, all_ifaces},
+    Iface,
+};
 use crate::{net::iface::sched::PollScheduler, prelude::*};
 
-pub static IFACES: Once<Vec<Arc<Iface>>> = Once::new();
-
 pub fn init() {
-    IFACES.call_once(|| {
-        let mut ifaces = Vec::with_capacity(2);
-
-        if let Some(iface_virtio) = new_virtio() {
-            ifaces.push(iface_virtio);
+    if let Some(iface_virtio) = new_virtio() {
+        add_boot_iface(iface_virtio);
+    }
+    add_boot_iface(new_loopback());
+    for name in aster_ethernet::all_ports() {
+        if let Some(iface_ethernet) = new_ethernet(name) {
+            add_boot_iface(iface_ethernet);
         }
-        ifaces.push(new_loopback());
-
-        ifaces
-    });
+    }
 
     for (name, _) in aster_network::all_devices() {
-        let callback = || {
-            // TODO: further check that the irq num is the same as iface's irq num
-            let iface_virtio = &IFACES.get().unwrap()[0];
-            iface_virtio.poll();
+        // TODO: further check that the irq num is the same as iface's irq num
+        let iface_name = if name == aster_virtio::device::network::DEVICE_NAME {
+            "virtio"
+        } else {
+            name.as_str()
+        };
+        let Some(iface) = all_ifaces()
+            .into_iter()
+            .find(|iface| iface.name() == iface_name)
+        else {
+            continue;
+        };
+        let callback = move || {
+            iface.poll();
         };
-        aster_network::register_recv_callback(&name, callback);
+        aster_network::register_recv_callback(&name, callback.clone());
         aster_network::register_send_callback(&name, callback);
     }
 
@@ -41,7 +52,6 @@ fn new_virtio() -> Option<Arc<Iface>> {
         iface::EtherIface,
         wire::{EthernetAddress, Ipv4Address, Ipv4Cidr},
     };
-    use aster_network::AnyNetworkDevice;
     use aster_virtio::device::network::DEVICE_NAME;
 
     const VIRTIO_ADDRESS: Ipv4Address = Ipv4Address::new(10, 0, 2, 15);
@@ -52,39 +62,60 @@ fn new_virtio() -> Option<Arc<Iface>> {
 
     let ether_addr = virtio_net.lock().mac_addr().0;
 
-    struct Wrapper(Arc<SpinLock<dyn AnyNetworkDevice, LocalIrqDisabled>>);
+    Some(EtherIface::new(
+        NetworkDeviceWrapper(virtio_net),
+        EthernetAddress(ether_addr),
+        Some(Ipv4Cidr::new(VIRTIO_ADDRESS, VIRTIO_ADDRESS_PREFIX_LEN)),
+        Some(VIRTIO_GATEWAY),
+        "virtio".to_owned(),
+        PollScheduler::new(),
+    ))
+}
 
-    impl WithDevice for Wrapper {
-        type Device = dyn AnyNetworkDevice;
+/// Creates the interface of an Ethernet port, which has no IPv4 address until one is
+/// configured.
+fn new_ethernet(name: String) -> Option<Arc<Iface>> {
+    use aster_bigtcp::{iface::EtherIface, wire::EthernetAddress};
 
-        fn with<F, R>(&self, f: F) -> R
-        where
-            F: FnOnce(&mut Self::Device) -> R,
-        {
-            let mut device = self.0.lock();
-            f(&mut *device)
-        }
-    }
+    let device = aster_network::get_device(&name)?;
+
+    let ether_addr = device.lock().mac_addr().0;
 
     Some(EtherIface::new(
-        Wrapper(virtio_net),
+        NetworkDeviceWrapper(device),
         EthernetAddress(ether_addr),
-        Ipv4Cidr::new(VIRTIO_ADDRESS, VIRTIO_ADDRESS_PREFIX_LEN),
-        VIRTIO_GATEWAY,
-        "virtio".to_owned(),
+        None,
+        None,
+        name,
         PollScheduler::new(),
     ))
 }
 
+struct NetworkDeviceWrapper(Arc<SpinLock<dyn AnyNetworkDevice, LocalIrqDisabled>>);
+
+impl WithDevice for NetworkDeviceWrapper {
+    type Device = dyn AnyNetworkDevice;
+
+    fn with<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut Self::Device) -> R,
+    {
+        let mut device = self.0.lock();
+        f(&mut *device)
+    }
+}
+
 fn new_loopback() -> Arc<Iface> {
     use aster_bigtcp::{
         device::{Loopback, Medium},
-        iface::IpIface,
-        wire::{Ipv4Address, Ipv4Cidr},
+        iface::{InterfaceFlags, InterfaceType, IpIface},
+        wire::{Ipv4Address, Ipv4Cidr, Ipv6Address, Ipv6Cidr},
     };
 
     const LOOPBACK_ADDRESS: Ipv4Address = Ipv4Address::new(127, 0, 0, 1);
     const LOOPBACK_ADDRESS_PREFIX_LEN: u8 = 8; // mask: 255.0.0.0
+    const LOOPBACK_IPV6_ADDRESS: Ipv6Address = Ipv6Address::LOCALHOST; // ::1
+    const LOOPBACK_IPV6_ADDRESS_PREFIX_LEN: u8 = 128;
 
     struct Wrapper(Mutex<Loopback>);
 
@@ -100,10 +131,23 @@ fn new_loopback() -> Arc<Iface> {
         }
     }
 
-    IpIface::new(
+    let iface: Arc<Iface> = IpIface::new(
         Wrapper(Mutex::new(Loopback::new(Medium::Ip))),
-        Ipv4Cidr::new(LOOPBACK_ADDRESS, LOOPBACK_ADDRESS_PREFIX_LEN),
+        Some(Ipv4Cidr::new(LOOPBACK_ADDRESS, LOOPBACK_ADDRESS_PREFIX_LEN)),
         "lo".to_owned(),
+        InterfaceType::Loopback,
+        InterfaceFlags::UP
+            | InterfaceFlags::LOOPBACK
+            | InterfaceFlags::RUNNING
+            | InterfaceFlags::LOWER_UP,
         PollScheduler::new(),
-    ) as _
+    );
+    iface
+        .add_ipv6_cidr(Ipv6Cidr::new(
+            LOOPBACK_IPV6_ADDRESS,
+            LOOPBACK_IPV6_ADDRESS_PREFIX_LEN,
+        ))
+        .unwrap();
+
+    iface
 }
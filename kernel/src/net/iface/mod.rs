@@ -1,12 +1,25 @@
 // SPDX-License-Identifier: MPL-2.0
 
+mod bridge;
 mod ext;
+mod filter;
 mod init;
 mod poll;
+mod registry;
 mod sched;
+mod tun;
+mod wireguard;
 
-pub use init::{init, IFACES};
+pub use bridge::Bridge;
+pub use filter::FILTER_TABLE;
+pub use init::init;
 pub use poll::lazy_init;
+pub use registry::{all_ifaces, find_iface_by_index, find_iface_by_name, IFNAMSIZ};
+pub use tun::{TunIface, TunKind};
+pub use wireguard::{
+    WireGuard, WireGuardConfig, WireGuardKey, WireGuardPeerConfig, WireGuardPeerUpdate,
+    WireGuardUpdate,
+};
 
 pub type Iface = dyn aster_bigtcp::iface::Iface<ext::BigtcpExt>;
 pub type BoundPort = aster_bigtcp::iface::BoundPort<ext::BigtcpExt>;
@@ -16,3 +29,5 @@ pub type RawTcpSocketExt = aster_bigtcp::socket::RawTcpSocketExt<ext::BigtcpExt>
 pub type TcpConnection = aster_bigtcp::socket::TcpConnection<ext::BigtcpExt>;
 pub type TcpListener = aster_bigtcp::socket::TcpListener<ext::BigtcpExt>;
 pub type UdpSocket = aster_bigtcp::socket::UdpSocket<ext::BigtcpExt>;
+pub type RawIpSocket = aster_bigtcp::socket::RawIpSocket<ext::BigtcpExt>;
+pub type LinkSocket = aster_bigtcp::socket::LinkSocket<ext::BigtcpExt>;
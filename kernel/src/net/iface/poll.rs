@@ -6,7 +6,7 @@ use core::time::Duration;
 use log::trace;
 use ostd::timer::Jiffies;
 
-use super::{Iface, IFACES};
+use super::{registry::all_ifaces, Iface};
 use crate::{
     sched::{Nice, SchedPolicy},
     thread::kernel_thread::ThreadOptions,
@@ -14,20 +14,18 @@ use crate::{
 };
 
 pub fn lazy_init() {
-    for iface in IFACES.get().unwrap() {
-        spawn_background_poll_thread(iface.clone());
+    for iface in all_ifaces() {
+        spawn_background_poll_thread(iface);
     }
 }
 
 pub(super) fn poll_ifaces() {
-    let ifaces = IFACES.get().unwrap();
-
-    for iface in ifaces.iter() {
+    for iface in all_ifaces() {
         iface.poll();
     }
 }
 
-fn spawn_background_poll_thread(iface: Arc<Iface>) {
+pub(super) fn spawn_background_poll_thread(iface: Arc<Iface>) {
     let task_fn = move || {
         trace!("spawn background poll thread for {}", iface.name());
 
@@ -35,10 +33,15 @@ fn spawn_background_poll_thread(iface: Arc<Iface>) {
         let wait_queue = sched_poll.polling_wait_queue();
 
         loop {
-            let next_poll_at_ms = if let Some(next_poll_at_ms) = sched_poll.next_poll_at_ms() {
-                next_poll_at_ms
-            } else {
-                wait_queue.wait_until(|| sched_poll.next_poll_at_ms())
+            // The thread exits when the iface is removed (see `remove_iface`).
+            let Some(next_poll_at_ms) = wait_queue.wait_until(|| {
+                if sched_poll.is_removed() {
+                    Some(None)
+                } else {
+                    sched_poll.next_poll_at_ms().map(Some)
+                }
+            }) else {
+                break;
             };
 
             let now_as_ms = Jiffies::elapsed().as_duration().as_millis() as u64;
@@ -58,9 +61,12 @@ fn spawn_background_poll_thread(iface: Arc<Iface>) {
 
             let duration = Duration::from_millis(next_poll_at_ms - now_as_ms);
             let _ = wait_queue.wait_until_or_timeout(
-                // If `sched_poll.next_poll_at_ms()` changes to an earlier time, we will end the
-                // waiting.
-                || (sched_poll.next_poll_at_ms()? < next_poll_at_ms).then_some(()),
+                // If `sched_poll.next_poll_at_ms()` changes to an earlier time or the iface is
+                // removed, we will end the waiting.
+                || {
+                    (sched_poll.is_removed() || sched_poll.next_poll_at_ms()? < next_poll_at_ms)
+                        .then_some(())
+                },
                 &duration,
             );
         }
@@ -0,0 +1,118 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::format;
+
+use super::{poll::spawn_background_poll_thread, Iface};
+use crate::prelude::*;
+
+/// All the ifaces.
+///
+/// The ifaces created during the boot come first, followed by the ifaces created by the user
+/// space (e.g., TUN/TAP devices and bridges).
+static IFACES: RwLock<Vec<Arc<Iface>>> = RwLock::new(Vec::new());
+
+/// Returns all the ifaces.
+///
+/// The first iface is the default iface, through which packets are sent if no other iface can
+/// reach the destination.
+pub fn all_ifaces() -> Vec<Arc<Iface>> {
+    IFACES.read().clone()
+}
+
+/// Finds the iface whose index is `index`.
+pub fn find_iface_by_index(index: u32) -> Option<Arc<Iface>> {
+    IFACES
+        .read()
+        .iter()
+        .find(|iface| iface.index() == index)
+        .cloned()
+}
+
+/// Finds the iface whose name is `name`.
+pub fn find_iface_by_name(name: &str) -> Option<Arc<Iface>> {
+    IFACES
+        .read()
+        .iter()
+        .find(|iface| iface.name() == name)
+        .cloned()
+}
+
+/// Adds an iface that is created during the boot.
+///
+/// The iface will be polled in the background after [`super::lazy_init`] is called.
+pub(super) fn add_boot_iface(iface: Arc<Iface>) {
+    IFACES.write().push(iface);
+}
+
+/// Creates an iface with `new_iface` and adds it.
+///
+/// If `name` contains `%d`, it is replaced with the smallest number that makes the name unique,
+/// like Linux does. Otherwise, this method fails with [`Errno::EEXIST`] if there is already an
+/// iface with the same name.
+///
+/// The new iface will be polled in the background, so this method should only be called after
+/// [`super::lazy_init`].
+pub(super) fn add_iface<F>(name: &str, new_iface: F) -> Result<Arc<Iface>>
+where
+    F: FnOnce(String) -> Result<Arc<Iface>>,
+{
+    if !is_valid_name(name) {
+        return_errno_with_message!(Errno::EINVAL, "the iface name is invalid");
+    }
+
+    let mut ifaces = IFACES.write();
+
+    let is_used = |name: &str| ifaces.iter().any(|iface| iface.name() == name);
+    let name = if let Some((prefix, suffix)) = name.split_once("%d") {
+        if suffix.contains('%') {
+            return_errno_with_message!(Errno::EINVAL, "the iface name is invalid");
+        }
+        let Some(name) = (0..MAX_NAME_UNITS)
+            .map(|unit| format!("{}{}{}", prefix, unit, suffix))
+            .find(|name| name.len() < IFNAMSIZ && !is_used(name))
+        else {
+            return_errno_with_message!(Errno::ENFILE, "no iface name is available");
+        };
+        name
+    } else if is_used(name) {
+        return_errno_with_message!(Errno::EEXIST, "the iface name is already in use");
+    } else {
+        name.to_string()
+    };
+
+    let iface = new_iface(name)?;
+    ifaces.push(iface.clone());
+    drop(ifaces);
+
+    spawn_background_poll_thread(iface.clone());
+
+    Ok(iface)
+}
+
+/// Removes an iface that is added by [`add_iface`].
+///
+/// The iface will no longer be polled in the background. Note that the iface may still be used
+/// by the sockets that are bound to it, but no new sockets can be bound to it.
+pub(super) fn remove_iface(iface: &Arc<Iface>) {
+    IFACES.write().retain(|other| !Arc::ptr_eq(other, iface));
+    iface.sched_poll().mark_removed();
+}
+
+/// Returns whether `name` can be used as an iface name (or a template of iface names).
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/net/core/dev.c#L1030>.
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() < IFNAMSIZ
+        && name != "."
+        && name != ".."
+        && !name.contains(|c: char| c == '/' || c == ':' || c.is_whitespace())
+}
+
+/// The maximum length of iface names, including the null terminator.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/if.h#L33>.
+pub const IFNAMSIZ: usize = 16;
+
+/// The maximum number of ifaces that are named with the same template.
+const MAX_NAME_UNITS: usize = 1024;
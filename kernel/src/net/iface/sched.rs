@@ -1,6 +1,6 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use core::sync::atomic::{AtomicU64, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 
 use aster_bigtcp::iface::ScheduleNextPoll;
 use ostd::sync::WaitQueue;
@@ -11,6 +11,8 @@ pub struct PollScheduler {
     next_poll_at_ms: AtomicU64,
     /// The wait queue that the background polling thread will sleep on.
     polling_wait_queue: WaitQueue,
+    /// Whether the iface has been removed, in which case the background polling thread exits.
+    is_removed: AtomicBool,
 }
 
 impl PollScheduler {
@@ -18,6 +20,7 @@ impl PollScheduler {
         Self {
             next_poll_at_ms: AtomicU64::new(0),
             polling_wait_queue: WaitQueue::new(),
+            is_removed: AtomicBool::new(false),
         }
     }
 
@@ -33,6 +36,16 @@ impl PollScheduler {
     pub(super) fn polling_wait_queue(&self) -> &WaitQueue {
         &self.polling_wait_queue
     }
+
+    pub(super) fn is_removed(&self) -> bool {
+        self.is_removed.load(Ordering::Relaxed)
+    }
+
+    /// Marks the iface as removed and stops the background polling thread.
+    pub(super) fn mark_removed(&self) {
+        self.is_removed.store(true, Ordering::Relaxed);
+        self.polling_wait_queue.wake_all();
+    }
 }
 
 impl ScheduleNextPoll for PollScheduler {
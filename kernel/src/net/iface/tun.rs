@@ -0,0 +1,479 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! TUN/TAP devices.
+//!
+//! A TUN/TAP device is a virtual iface whose packets are exchanged with a user-space program
+//! (e.g., a VPN client or a virtual machine monitor) instead of the hardware. Packets sent by
+//! the iface can be read from the device file, and packets written to the device file are
+//! received by the iface. A TUN device exchanges IP packets, while a TAP device exchanges
+//! Ethernet frames.
+//!
+//! Reference: <https://docs.kernel.org/networking/tuntap.html>.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use aster_bigtcp::{
+    device::{Device, DeviceCapabilities, Medium, NotifyDevice, RxToken, TxToken, WithDevice},
+    iface::{EtherIface, InterfaceFlags, InterfaceType, IpIface},
+    time::Instant,
+    wire::EthernetAddress,
+};
+use ostd::sync::LocalIrqDisabled;
+
+use super::{
+    bridge::Bridge,
+    registry::{add_iface, find_iface_by_name, remove_iface},
+    sched::PollScheduler,
+    Iface,
+};
+use crate::{
+    events::IoEvents,
+    prelude::*,
+    process::signal::{PollHandle, Pollee},
+    util::random::getrandom,
+};
+
+/// The kind of a TUN/TAP device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TunKind {
+    /// A TUN device, which exchanges IP packets
+    Tun,
+    /// A TAP device, which exchanges Ethernet frames
+    Tap,
+}
+
+/// A TUN/TAP device.
+pub struct TunIface {
+    iface: Arc<Iface>,
+    kind: TunKind,
+    queue: Arc<PacketQueue>,
+    /// Whether the device is attached to a device file
+    is_attached: AtomicBool,
+    /// Whether the device is kept after the device file is closed (see `TUNSETPERSIST`)
+    is_persistent: AtomicBool,
+    /// Whether the device has been removed
+    is_removed: AtomicBool,
+    /// The bridge that the device is a port of, if any
+    master: Mutex<Option<Arc<Bridge>>>,
+}
+
+/// All the TUN/TAP devices.
+static TUN_IFACES: Mutex<Vec<Arc<TunIface>>> = Mutex::new(Vec::new());
+
+impl TunIface {
+    /// Attaches to the device named `name`, creating it if it does not exist.
+    ///
+    /// If `name` contains `%d`, a new device is always created (see [`add_iface`]).
+    pub fn attach(name: &str, kind: TunKind) -> Result<Arc<Self>> {
+        let mut tun_ifaces = TUN_IFACES.lock();
+
+        if let Some(tun) = tun_ifaces.iter().find(|tun| tun.iface.name() == name) {
+            if tun.kind != kind {
+                return_errno_with_message!(Errno::EINVAL, "the device has a different kind");
+            }
+            if tun.is_attached.swap(true, Ordering::Relaxed) {
+                return_errno_with_message!(Errno::EBUSY, "the device is already attached");
+            }
+            return Ok(tun.clone());
+        }
+        if find_iface_by_name(name).is_some() {
+            return_errno_with_message!(Errno::EINVAL, "the iface is not a TUN/TAP device");
+        }
+
+        let queue = Arc::new(PacketQueue::new());
+        let ether_addr = random_ether_addr()?;
+        let iface = add_iface(name, |name| {
+            Ok(new_iface(name, kind, queue.clone(), ether_addr))
+        })?;
+
+        let tun = Arc::new(Self {
+            iface,
+            kind,
+            queue,
+            is_attached: AtomicBool::new(true),
+            is_persistent: AtomicBool::new(false),
+            is_removed: AtomicBool::new(false),
+            master: Mutex::new(None),
+        });
+        tun_ifaces.push(tun.clone());
+
+        Ok(tun)
+    }
+
+    /// Detaches from the device.
+    ///
+    /// The device is removed unless it is persistent.
+    pub fn detach(&self) {
+        self.is_attached.store(false, Ordering::Relaxed);
+
+        if !self.is_persistent.load(Ordering::Relaxed) {
+            self.remove();
+        }
+    }
+
+    /// Finds the TUN/TAP device whose iface index is `index`.
+    pub fn find(index: u32) -> Option<Arc<Self>> {
+        TUN_IFACES
+            .lock()
+            .iter()
+            .find(|tun| tun.iface.index() == index)
+            .cloned()
+    }
+
+    /// Removes the device.
+    ///
+    /// The device file that is attached to the device (if any) can no longer be used.
+    pub fn remove(&self) {
+        let mut tun_ifaces = TUN_IFACES.lock();
+        if self.is_removed.swap(true, Ordering::Relaxed) {
+            return;
+        }
+        tun_ifaces.retain(|tun| !Arc::ptr_eq(&tun.iface, &self.iface));
+        drop(tun_ifaces);
+
+        if let Some(bridge) = self.master.lock().take() {
+            bridge.remove_port(self.iface.index());
+        }
+        remove_iface(&self.iface);
+
+        self.queue.pollee.notify(IoEvents::ERR);
+    }
+
+    pub fn iface(&self) -> &Arc<Iface> {
+        &self.iface
+    }
+
+    pub fn kind(&self) -> TunKind {
+        self.kind
+    }
+
+    pub fn is_removed(&self) -> bool {
+        self.is_removed.load(Ordering::Relaxed)
+    }
+
+    pub fn is_persistent(&self) -> bool {
+        self.is_persistent.load(Ordering::Relaxed)
+    }
+
+    pub fn set_persistent(&self, is_persistent: bool) {
+        self.is_persistent.store(is_persistent, Ordering::Relaxed);
+    }
+
+    /// Returns the bridge that the device is a port of.
+    pub fn master(&self) -> Option<Arc<Bridge>> {
+        self.master.lock().clone()
+    }
+
+    /// Makes the device a port of the bridge, or releases it from its bridge if `master` is
+    /// `None`.
+    pub fn set_master(&self, master: Option<Arc<Bridge>>) -> Result<()> {
+        if self.kind != TunKind::Tap {
+            return_errno_with_message!(
+                Errno::EOPNOTSUPP,
+                "only TAP devices can be ports of bridges"
+            );
+        }
+
+        let mut old_master = self.master.lock();
+        // This is checked with the lock held, so a removed device cannot become a port again.
+        if self.is_removed() {
+            return_errno_with_message!(Errno::ENODEV, "the device has been removed");
+        }
+        match (old_master.as_ref(), master.as_ref()) {
+            (Some(old), Some(new)) if Arc::ptr_eq(old, new) => return Ok(()),
+            (Some(_), Some(_)) => {
+                return_errno_with_message!(Errno::EBUSY, "the device is a port of another bridge")
+            }
+            _ => (),
+        }
+
+        if let Some(new) = master.as_ref() {
+            new.add_port(self.iface.index(), self.queue.clone())?;
+        }
+        if let Some(old) = old_master.take() {
+            old.remove_port(self.iface.index());
+        }
+        *old_master = master;
+
+        Ok(())
+    }
+
+    /// Releases the device from the bridge if the device is a port of it.
+    pub(super) fn release_from(&self, bridge: &Arc<Bridge>) {
+        let mut master = self.master.lock();
+        if master
+            .as_ref()
+            .is_some_and(|master| Arc::ptr_eq(master, bridge))
+        {
+            *master = None;
+        }
+    }
+
+    /// Receives a packet that is sent by the iface.
+    pub fn try_recv(&self) -> Option<Vec<u8>> {
+        let packet = self.queue.tx.lock().pop_front();
+        self.queue.pollee.invalidate();
+        packet
+    }
+
+    /// Sends a packet to the iface, as if the packet were received from the link.
+    ///
+    /// If the device is a port of a bridge, the packet is forwarded by the bridge instead.
+    pub fn send(&self, packet: Vec<u8>) {
+        if let Some(bridge) = self.master() {
+            bridge.forward_from_port(self.iface.index(), &packet);
+            return;
+        }
+
+        self.queue.push_rx(packet);
+        self.iface.poll();
+    }
+
+    pub fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
+        self.queue.pollee.poll_with(mask, poller, || {
+            if self.is_removed() {
+                return IoEvents::ERR;
+            }
+
+            let mut events = IoEvents::OUT;
+            if !self.queue.tx.lock().is_empty() {
+                events |= IoEvents::IN;
+            }
+            events
+        })
+    }
+}
+
+fn new_iface(
+    name: String,
+    kind: TunKind,
+    queue: Arc<PacketQueue>,
+    ether_addr: EthernetAddress,
+) -> Arc<Iface> {
+    match kind {
+        TunKind::Tun => IpIface::new(
+            QueueDriver::new(QueueDevice::new(queue, Medium::Ip)),
+            None,
+            name,
+            InterfaceType::None,
+            InterfaceFlags::UP
+                | InterfaceFlags::POINTOPOINT
+                | InterfaceFlags::RUNNING
+                | InterfaceFlags::NOARP
+                | InterfaceFlags::MULTICAST
+                | InterfaceFlags::LOWER_UP,
+            PollScheduler::new(),
+        ),
+        TunKind::Tap => EtherIface::new(
+            QueueDriver::new(QueueDevice::new(queue, Medium::Ethernet)),
+            ether_addr,
+            None,
+            None,
+            name,
+            PollScheduler::new(),
+        ),
+    }
+}
+
+/// Generates a random, locally administered unicast Ethernet address.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/linux/etherdevice.h#L226>.
+pub(super) fn random_ether_addr() -> Result<EthernetAddress> {
+    let mut addr = [0u8; 6];
+    getrandom(&mut addr)?;
+    addr[0] = (addr[0] & !0x01) | 0x02;
+
+    Ok(EthernetAddress(addr))
+}
+
+/// The packets that are exchanged between a virtual device and the user space (or other
+/// virtual devices).
+pub(super) struct PacketQueue {
+    /// The packets that are sent by the iface (e.g., to be read by the user space)
+    tx: SpinLock<VecDeque<Vec<u8>>, LocalIrqDisabled>,
+    /// The packets that will be received by the iface (e.g., written by the user space)
+    rx: SpinLock<VecDeque<Vec<u8>>, LocalIrqDisabled>,
+    pollee: Pollee,
+}
+
+impl PacketQueue {
+    pub(super) fn new() -> Self {
+        Self {
+            tx: SpinLock::new(VecDeque::new()),
+            rx: SpinLock::new(VecDeque::new()),
+            pollee: Pollee::new(),
+        }
+    }
+
+    /// Pushes a packet that is sent by the iface.
+    ///
+    /// Like Linux, the packet is dropped if there are too many packets that are not consumed
+    /// yet.
+    pub(super) fn push_tx(&self, packet: Vec<u8>) {
+        let mut tx = self.tx.lock();
+        if tx.len() >= MAX_QUEUED_PACKETS {
+            return;
+        }
+        tx.push_back(packet);
+        drop(tx);
+
+        self.pollee.notify(IoEvents::IN);
+    }
+
+    /// Pushes a packet that will be received by the iface.
+    pub(super) fn push_rx(&self, packet: Vec<u8>) {
+        let mut rx = self.rx.lock();
+        if rx.len() >= MAX_QUEUED_PACKETS {
+            return;
+        }
+        rx.push_back(packet);
+    }
+}
+
+/// The maximum number of packets in each direction of a [`PacketQueue`].
+///
+/// This is the same as the default length of the transmit queue of TUN/TAP devices in Linux.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/drivers/net/tun.c>.
+pub(super) const MAX_QUEUED_PACKETS: usize = 500;
+
+/// A device whose packets are exchanged through a [`PacketQueue`].
+///
+/// The packets sent by the device are handled by the `on_transmit` callback. By default, they
+/// are pushed to the queue so that the user space can read them.
+pub(super) struct QueueDevice<T = ToUser> {
+    queue: Arc<PacketQueue>,
+    medium: Medium,
+    mtu: usize,
+    on_transmit: T,
+}
+
+/// Transmits packets to the user space.
+pub(super) struct ToUser;
+
+/// A callback that handles the packets sent by a [`QueueDevice`].
+pub(super) trait OnTransmit: Send + Sync {
+    fn on_transmit(&self, queue: &PacketQueue, packet: Vec<u8>);
+}
+
+impl OnTransmit for ToUser {
+    fn on_transmit(&self, queue: &PacketQueue, packet: Vec<u8>) {
+        queue.push_tx(packet);
+    }
+}
+
+impl QueueDevice {
+    fn new(queue: Arc<PacketQueue>, medium: Medium) -> Self {
+        Self::with_on_transmit(queue, medium, ToUser)
+    }
+}
+
+impl<T: OnTransmit> QueueDevice<T> {
+    pub(super) fn with_on_transmit(
+        queue: Arc<PacketQueue>,
+        medium: Medium,
+        on_transmit: T,
+    ) -> Self {
+        Self {
+            queue,
+            medium,
+            mtu: DEFAULT_MTU,
+            on_transmit,
+        }
+    }
+
+    /// Sets the MTU of the device, which is [`DEFAULT_MTU`] by default.
+    pub(super) fn with_mtu(mut self, mtu: usize) -> Self {
+        self.mtu = mtu;
+        self
+    }
+}
+
+impl<T: OnTransmit> Device for QueueDevice<T> {
+    type RxToken<'a>
+        = QueueRxToken
+    where
+        Self: 'a;
+    type TxToken<'a>
+        = QueueTxToken<'a, T>
+    where
+        Self: 'a;
+
+    fn receive(&mut self, _timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        let packet = self.queue.rx.lock().pop_front()?;
+        Some((QueueRxToken(packet), QueueTxToken(self)))
+    }
+
+    fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        Some(QueueTxToken(self))
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut caps = DeviceCapabilities::default();
+        caps.medium = self.medium;
+        caps.max_transmission_unit = match self.medium {
+            Medium::Ethernet => self.mtu + ETHERNET_HEADER_LEN,
+            Medium::Ip => self.mtu,
+        };
+        caps
+    }
+}
+
+impl<T> NotifyDevice for QueueDevice<T> {
+    fn notify_poll_end(&mut self) {}
+}
+
+/// The default MTU of virtual devices.
+const DEFAULT_MTU: usize = 1500;
+
+/// The length of Ethernet headers.
+const ETHERNET_HEADER_LEN: usize = 14;
+
+pub(super) struct QueueRxToken(Vec<u8>);
+
+impl RxToken for QueueRxToken {
+    fn consume<R, F>(self, f: F) -> R
+    where
+        F: FnOnce(&[u8]) -> R,
+    {
+        f(&self.0)
+    }
+}
+
+pub(super) struct QueueTxToken<'a, T>(&'a QueueDevice<T>);
+
+impl<T: OnTransmit> TxToken for QueueTxToken<'_, T> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let mut packet = vec![0u8; len];
+        let res = f(&mut packet);
+
+        let device = self.0;
+        device.on_transmit.on_transmit(&device.queue, packet);
+
+        res
+    }
+}
+
+/// A driver that protects a [`QueueDevice`] with a spin lock.
+pub(super) struct QueueDriver<T>(SpinLock<QueueDevice<T>, LocalIrqDisabled>);
+
+impl<T> QueueDriver<T> {
+    pub(super) fn new(device: QueueDevice<T>) -> Self {
+        Self(SpinLock::new(device))
+    }
+}
+
+impl<T: OnTransmit + 'static> WithDevice for QueueDriver<T> {
+    type Device = QueueDevice<T>;
+
+    fn with<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut Self::Device) -> R,
+    {
+        let mut device = self.0.lock();
+        f(&mut device)
+    }
+}
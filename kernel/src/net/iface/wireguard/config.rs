@@ -0,0 +1,87 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The configuration of WireGuard ifaces.
+//!
+//! The types mirror the attributes of the `wireguard` generic netlink family.
+//!
+//! Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/wireguard.h>.
+
+use core::time::Duration;
+
+use aster_bigtcp::wire::{IpCidr, IpEndpoint};
+
+use super::WireGuardKey;
+use crate::prelude::*;
+
+/// The configuration of a WireGuard iface (see `WG_CMD_GET_DEVICE`).
+#[derive(Debug)]
+pub struct WireGuardConfig {
+    pub private_key: Option<WireGuardKey>,
+    pub public_key: Option<WireGuardKey>,
+    pub listen_port: u16,
+    pub fwmark: u32,
+    pub peers: Vec<WireGuardPeerConfig>,
+}
+
+/// The configuration and the statistics of a WireGuard peer.
+#[derive(Debug)]
+pub struct WireGuardPeerConfig {
+    pub public_key: WireGuardKey,
+    pub preshared_key: WireGuardKey,
+    pub endpoint: Option<IpEndpoint>,
+    pub persistent_keepalive_interval: u16,
+    /// The time of the last completed handshake, in the real time
+    pub last_handshake_time: Option<Duration>,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub allowed_ips: Vec<IpCidr>,
+}
+
+/// The changes to the configuration of a WireGuard iface (see `WG_CMD_SET_DEVICE`).
+///
+/// The fields that are `None` are left unchanged.
+#[derive(Debug, Default)]
+pub struct WireGuardUpdate {
+    /// The new private key, where all zeros remove the private key
+    pub private_key: Option<WireGuardKey>,
+    /// The new listening port, where zero means an ephemeral port
+    pub listen_port: Option<u16>,
+    pub fwmark: Option<u32>,
+    /// Whether to remove all the peers before applying `peers` (see `WGDEVICE_F_REPLACE_PEERS`)
+    pub replace_peers: bool,
+    pub peers: Vec<WireGuardPeerUpdate>,
+}
+
+/// The changes to a WireGuard peer.
+///
+/// The peer is created if it does not exist, unless `update_only` is set.
+#[derive(Debug)]
+pub struct WireGuardPeerUpdate {
+    pub public_key: WireGuardKey,
+    /// Whether to remove the peer (see `WGPEER_F_REMOVE_ME`)
+    pub remove: bool,
+    /// Whether to skip the peer if it does not exist (see `WGPEER_F_UPDATE_ONLY`)
+    pub update_only: bool,
+    pub preshared_key: Option<WireGuardKey>,
+    pub endpoint: Option<IpEndpoint>,
+    pub persistent_keepalive_interval: Option<u16>,
+    /// Whether to remove all the allowed IPs before adding `allowed_ips` (see
+    /// `WGPEER_F_REPLACE_ALLOWEDIPS`)
+    pub replace_allowed_ips: bool,
+    pub allowed_ips: Vec<IpCidr>,
+}
+
+impl WireGuardPeerUpdate {
+    pub fn new(public_key: WireGuardKey) -> Self {
+        Self {
+            public_key,
+            remove: false,
+            update_only: false,
+            preshared_key: None,
+            endpoint: None,
+            persistent_keepalive_interval: None,
+            replace_allowed_ips: false,
+            allowed_ips: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,844 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! WireGuard tunnels.
+//!
+//! A WireGuard iface is a virtual point-to-point iface. The packets sent by the iface are
+//! encrypted and sent to the peers over UDP, and the encrypted packets received from the peers
+//! are decrypted and then received by the iface. Each packet is sent to the peer whose allowed
+//! IPs contain its destination address, and a received packet is only accepted if its source
+//! address is allowed for the peer that sends it (i.e., cryptokey routing).
+//!
+//! The ifaces are created and removed by `RTM_NEWLINK` and `RTM_DELLINK`, and they are
+//! configured with the `wireguard` generic netlink family, like Linux.
+//!
+//! Reference: <https://www.wireguard.com/papers/wireguard.pdf>.
+
+mod config;
+mod noise;
+mod peer;
+mod transport;
+
+use core::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use aster_bigtcp::{
+    device::Medium,
+    iface::{InterfaceFlags, InterfaceType, IpIface},
+    wire::{IpAddress, IpCidr, IpEndpoint, IpVersion, Ipv4Packet, Ipv6Packet},
+};
+pub use config::{WireGuardConfig, WireGuardPeerConfig, WireGuardPeerUpdate, WireGuardUpdate};
+use noise::{
+    HandshakeInitiation, HandshakeResponse, MessageType, StaticIdentity, Timestamp,
+    TransportHeader, KEY_LEN, TIMESTAMP_LEN, TRANSPORT_HEADER_LEN,
+};
+use ostd::{
+    sync::{LocalIrqDisabled, WaitQueue},
+    timer::Jiffies,
+};
+use peer::{Handshake, Peer, Session, KEEPALIVE_TIMEOUT, REKEY_ATTEMPT_TIME, REKEY_TIMEOUT};
+use transport::Transport;
+
+use super::{
+    registry::{add_iface, remove_iface},
+    sched::PollScheduler,
+    tun::{OnTransmit, PacketQueue, QueueDevice, QueueDriver, MAX_QUEUED_PACKETS},
+    Iface,
+};
+use crate::{
+    events::{IoEvents, Observer},
+    prelude::*,
+    process::signal::{PollAdaptor, Pollee},
+    sched::{Nice, SchedPolicy},
+    thread::kernel_thread::ThreadOptions,
+    time::clocks::RealTimeClock,
+    util::random::getrandom,
+};
+
+/// A WireGuard key (i.e., a private key, a public key, or a preshared key).
+pub type WireGuardKey = [u8; KEY_LEN];
+
+/// A WireGuard iface.
+pub struct WireGuard {
+    iface: Arc<Iface>,
+    core: Arc<WireGuardCore>,
+}
+
+/// All the WireGuard ifaces.
+static WIREGUARDS: Mutex<Vec<Arc<WireGuard>>> = Mutex::new(Vec::new());
+
+/// The MTU of WireGuard ifaces.
+///
+/// This is the default MTU in Linux, which leaves room for the IPv6 and UDP headers and the
+/// WireGuard header and tag in a 1500-byte packet.
+const WIREGUARD_MTU: usize = 1420;
+
+/// The interval between two checks of the timers.
+const TIMER_INTERVAL: Duration = Duration::from_secs(1);
+
+impl WireGuard {
+    /// Creates a WireGuard iface named `name`.
+    pub fn new(name: &str) -> Result<Arc<Self>> {
+        let queue = Arc::new(PacketQueue::new());
+        let signal = Arc::new(WorkerSignal::new());
+        let pollee = Pollee::new();
+
+        let iface = add_iface(name, |name| {
+            let device =
+                QueueDevice::with_on_transmit(queue.clone(), Medium::Ip, Encryptor(signal.clone()))
+                    .with_mtu(WIREGUARD_MTU);
+            let iface: Arc<Iface> = IpIface::new(
+                QueueDriver::new(device),
+                None,
+                name,
+                InterfaceType::None,
+                InterfaceFlags::UP
+                    | InterfaceFlags::POINTOPOINT
+                    | InterfaceFlags::RUNNING
+                    | InterfaceFlags::NOARP
+                    | InterfaceFlags::LOWER_UP,
+                PollScheduler::new(),
+            );
+            Ok(iface)
+        })?;
+
+        let core = Arc::new(WireGuardCore {
+            queue,
+            signal,
+            state: Mutex::new(DeviceState {
+                identity: None,
+                fwmark: 0,
+                peers: Vec::new(),
+                transport: Transport::new(pollee.clone()),
+            }),
+        });
+        spawn_worker(iface.clone(), core.clone(), pollee);
+
+        let wireguard = Arc::new(Self { iface, core });
+        WIREGUARDS.lock().push(wireguard.clone());
+
+        Ok(wireguard)
+    }
+
+    /// Finds the WireGuard iface whose iface index is `index`.
+    pub fn find(index: u32) -> Option<Arc<Self>> {
+        WIREGUARDS
+            .lock()
+            .iter()
+            .find(|wireguard| wireguard.iface.index() == index)
+            .cloned()
+    }
+
+    /// Removes the WireGuard iface.
+    ///
+    /// The UDP port is released immediately, and all the keys are erased.
+    pub fn remove(self: &Arc<Self>) {
+        WIREGUARDS
+            .lock()
+            .retain(|wireguard| !Arc::ptr_eq(wireguard, self));
+
+        self.core.signal.is_removed.store(true, Ordering::Relaxed);
+        self.core.signal.wait_queue.wake_all();
+
+        let mut state = self.core.state.lock();
+        state.identity = None;
+        state.peers.clear();
+        state.transport.close();
+        drop(state);
+
+        remove_iface(&self.iface);
+    }
+
+    pub fn iface(&self) -> &Arc<Iface> {
+        &self.iface
+    }
+
+    /// Returns the configuration of the iface.
+    pub fn config(&self) -> WireGuardConfig {
+        let state = self.core.state.lock();
+
+        let peers = state
+            .peers
+            .iter()
+            .map(|peer| WireGuardPeerConfig {
+                public_key: peer.public_key,
+                preshared_key: peer.preshared_key,
+                endpoint: peer.endpoint,
+                persistent_keepalive_interval: peer.persistent_keepalive_interval,
+                last_handshake_time: peer.last_handshake_time,
+                rx_bytes: peer.rx_bytes,
+                tx_bytes: peer.tx_bytes,
+                allowed_ips: peer.allowed_ips.clone(),
+            })
+            .collect();
+
+        WireGuardConfig {
+            private_key: state
+                .identity
+                .as_ref()
+                .map(|identity| *identity.private_key()),
+            public_key: state
+                .identity
+                .as_ref()
+                .map(|identity| *identity.public_key()),
+            listen_port: state.transport.port(),
+            fwmark: state.fwmark,
+            peers,
+        }
+    }
+
+    /// Updates the configuration of the iface.
+    ///
+    /// If this method fails, the configuration is left unchanged.
+    pub fn update(&self, update: WireGuardUpdate) -> Result<()> {
+        let mut state = self.core.state.lock();
+        if self.core.signal.is_removed.load(Ordering::Relaxed) {
+            return_errno_with_message!(Errno::ENODEV, "the iface has been removed");
+        }
+
+        if let Some(port) = update
+            .listen_port
+            .filter(|port| *port != state.transport.port())
+        {
+            state.transport.set_port(port, &self.iface)?;
+        }
+
+        if let Some(fwmark) = update.fwmark {
+            // TODO: Support routing the UDP packets by `fwmark`.
+            state.fwmark = fwmark;
+        }
+
+        if let Some(private_key) = update.private_key {
+            state.set_private_key(private_key);
+        }
+
+        if update.replace_peers {
+            state.peers.clear();
+        }
+
+        for peer_update in update.peers {
+            state.update_peer(peer_update);
+        }
+
+        Ok(())
+    }
+}
+
+/// The part of a WireGuard iface that is shared with the worker thread.
+struct WireGuardCore {
+    /// The decrypted packets that will be received by the iface
+    queue: Arc<PacketQueue>,
+    signal: Arc<WorkerSignal>,
+    state: Mutex<DeviceState>,
+}
+
+/// The signal that wakes up the worker thread.
+///
+/// This is shared with the iface and the UDP sockets (through [`WorkerWaker`]), which should
+/// not keep [`WireGuardCore`] alive.
+struct WorkerSignal {
+    /// The packets sent by the iface that wait to be encrypted
+    outgoing: SpinLock<VecDeque<Vec<u8>>, LocalIrqDisabled>,
+    /// Whether there are outgoing packets or received messages to handle
+    has_work: AtomicBool,
+    is_removed: AtomicBool,
+    wait_queue: WaitQueue,
+}
+
+impl WorkerSignal {
+    fn new() -> Self {
+        Self {
+            outgoing: SpinLock::new(VecDeque::new()),
+            has_work: AtomicBool::new(false),
+            is_removed: AtomicBool::new(false),
+            wait_queue: WaitQueue::new(),
+        }
+    }
+
+    fn wake(&self) {
+        self.has_work.store(true, Ordering::Release);
+        self.wait_queue.wake_all();
+    }
+}
+
+/// Queues the packets sent by the iface for encryption.
+struct Encryptor(Arc<WorkerSignal>);
+
+impl OnTransmit for Encryptor {
+    fn on_transmit(&self, _queue: &PacketQueue, packet: Vec<u8>) {
+        let mut outgoing = self.0.outgoing.lock();
+        if outgoing.len() >= MAX_QUEUED_PACKETS {
+            return;
+        }
+        outgoing.push_back(packet);
+        drop(outgoing);
+
+        self.0.wake();
+    }
+}
+
+/// Wakes up the worker thread when the UDP sockets receive messages.
+struct WorkerWaker(Arc<WorkerSignal>);
+
+impl Observer<IoEvents> for WorkerWaker {
+    fn on_events(&self, _events: &IoEvents) {
+        self.0.wake();
+    }
+}
+
+/// Spawns the thread that encrypts and decrypts the packets and handles the timers.
+///
+/// The thread exits when the iface is removed.
+fn spawn_worker(iface: Arc<Iface>, core: Arc<WireGuardCore>, pollee: Pollee) {
+    let task_fn = move || {
+        let signal = &core.signal;
+
+        let mut waker = PollAdaptor::with_observer(WorkerWaker(signal.clone()));
+        pollee.register_poller(waker.as_handle_mut(), IoEvents::IN);
+
+        let mut next_timer_at = Jiffies::elapsed().as_duration();
+        loop {
+            let _ = signal.wait_queue.wait_until_or_timeout(
+                || {
+                    (signal.has_work.swap(false, Ordering::Acquire)
+                        || signal.is_removed.load(Ordering::Relaxed))
+                    .then_some(())
+                },
+                &TIMER_INTERVAL,
+            );
+
+            let mut state = core.state.lock();
+            // This is checked with the lock held, so the sockets cannot be bound again after
+            // they are closed in `WireGuard::remove`.
+            if signal.is_removed.load(Ordering::Relaxed) {
+                break;
+            }
+
+            let now = Jiffies::elapsed().as_duration();
+            let mtu = iface.mtu();
+
+            let outgoing = core::mem::take(&mut *signal.outgoing.lock());
+            for packet in outgoing {
+                state.send_packet(packet, mtu, now);
+            }
+
+            let mut received = Vec::new();
+            while let Some((message, endpoint)) = state.transport.try_recv() {
+                if let Some(packet) = state.handle_message(message, endpoint, mtu, now) {
+                    received.push(packet);
+                }
+            }
+
+            if now >= next_timer_at {
+                state.handle_timers(&iface, mtu, now);
+                next_timer_at = now + TIMER_INTERVAL;
+            }
+            drop(state);
+
+            if !received.is_empty() {
+                for packet in received {
+                    core.queue.push_rx(packet);
+                }
+                iface.poll();
+            }
+        }
+    };
+
+    ThreadOptions::new(task_fn)
+        .sched_policy(SchedPolicy::Fair(Nice::MIN))
+        .spawn();
+}
+
+/// The state of a WireGuard iface.
+struct DeviceState {
+    identity: Option<StaticIdentity>,
+    fwmark: u32,
+    peers: Vec<Peer>,
+    transport: Transport,
+}
+
+impl DeviceState {
+    fn set_private_key(&mut self, private_key: WireGuardKey) {
+        let identity = (private_key != [0; KEY_LEN]).then(|| StaticIdentity::new(private_key));
+
+        let old_public_key = self
+            .identity
+            .as_ref()
+            .map(|identity| *identity.public_key());
+        let new_public_key = identity.as_ref().map(|identity| *identity.public_key());
+        if old_public_key != new_public_key {
+            // The sessions are derived from the old key, so they can no longer be used.
+            self.peers.iter_mut().for_each(Peer::clear_sessions);
+            // Like Linux, the peer that has our own public key is removed.
+            if let Some(public_key) = new_public_key {
+                self.peers.retain(|peer| peer.public_key != public_key);
+            }
+        }
+
+        self.identity = identity;
+    }
+
+    fn update_peer(&mut self, update: WireGuardPeerUpdate) {
+        // Like Linux, the peer that has our own public key is silently ignored.
+        if self
+            .identity
+            .as_ref()
+            .is_some_and(|identity| *identity.public_key() == update.public_key)
+        {
+            return;
+        }
+
+        let position = self
+            .peers
+            .iter()
+            .position(|peer| peer.public_key == update.public_key);
+        if update.remove {
+            if let Some(index) = position {
+                self.peers.remove(index);
+            }
+            return;
+        }
+        let index = match position {
+            Some(index) => index,
+            None if update.update_only => return,
+            None => {
+                self.peers.push(Peer::new(update.public_key));
+                self.peers.len() - 1
+            }
+        };
+
+        let peer = &mut self.peers[index];
+        if let Some(preshared_key) = update.preshared_key {
+            peer.preshared_key = preshared_key;
+        }
+        if let Some(endpoint) = update.endpoint {
+            peer.endpoint = Some(endpoint);
+        }
+        if let Some(interval) = update.persistent_keepalive_interval {
+            peer.persistent_keepalive_interval = interval;
+        }
+        if update.replace_allowed_ips {
+            peer.allowed_ips.clear();
+        }
+
+        for cidr in update.allowed_ips {
+            let cidr = canonicalize_cidr(cidr);
+            // Each CIDR belongs to one peer at most, so it is moved from other peers.
+            for peer in self.peers.iter_mut() {
+                peer.allowed_ips.retain(|allowed_ip| *allowed_ip != cidr);
+            }
+            self.peers[index].allowed_ips.push(cidr);
+        }
+    }
+
+    /// Returns the peer to which packets destined for `addr` are sent.
+    fn route(&self, addr: &IpAddress) -> Option<usize> {
+        self.peers
+            .iter()
+            .enumerate()
+            .filter_map(|(index, peer)| Some((index, peer.allowed_prefix_len(addr)?)))
+            .max_by_key(|(_, prefix_len)| *prefix_len)
+            .map(|(index, _)| index)
+    }
+
+    /// Encrypts a packet sent by the iface and sends it to its peer.
+    fn send_packet(&mut self, packet: Vec<u8>, mtu: usize, now: Duration) {
+        if self.identity.is_none() {
+            return;
+        }
+        let Some((_, dst_addr, _)) = parse_ip_header(&packet) else {
+            return;
+        };
+        let Some(index) = self.route(&dst_addr) else {
+            return;
+        };
+
+        if self.peers[index].needs_handshake(now) {
+            self.initiate_handshake(index, now);
+        }
+
+        let peer = &mut self.peers[index];
+        if !send_data(&self.transport, peer, &packet, mtu, now) {
+            peer.stage_packet(packet);
+        }
+    }
+
+    /// Sends a handshake initiation to the peer.
+    ///
+    /// If there is a pending handshake, it is replaced, but its start time is kept.
+    fn initiate_handshake(&mut self, index: usize, now: Duration) {
+        let Some(local_index) = self.allocate_index() else {
+            return;
+        };
+        let Some(ephemeral_private) = random_key() else {
+            return;
+        };
+
+        let Self {
+            identity: Some(identity),
+            peers,
+            transport,
+            ..
+        } = self
+        else {
+            return;
+        };
+        let peer = &mut peers[index];
+        let Some(endpoint) = peer.endpoint else {
+            return;
+        };
+
+        let Some((message, state)) = noise::create_initiation(
+            identity,
+            &peer.public_key,
+            ephemeral_private,
+            &tai64n_now(),
+            local_index,
+        ) else {
+            return;
+        };
+        transport.send(&endpoint, message.as_bytes());
+        peer.tx_bytes += size_of::<HandshakeInitiation>() as u64;
+        peer.last_sent = Some(now);
+
+        let started_at = peer
+            .handshake
+            .as_ref()
+            .map_or(now, |handshake| handshake.started_at);
+        peer.handshake = Some(Handshake {
+            state,
+            local_index,
+            sent_at: now,
+            started_at,
+        });
+    }
+
+    /// Allocates a random index that is not used by any peer.
+    fn allocate_index(&self) -> Option<u32> {
+        loop {
+            let mut bytes = [0u8; 4];
+            getrandom(&mut bytes).ok()?;
+            let index = u32::from_ne_bytes(bytes);
+
+            if !self.peers.iter().any(|peer| peer.owns_index(index)) {
+                return Some(index);
+            }
+        }
+    }
+
+    /// Handles a message received from `endpoint`.
+    ///
+    /// If the message carries a packet that should be received by the iface, this method
+    /// returns the packet.
+    fn handle_message(
+        &mut self,
+        message: Vec<u8>,
+        endpoint: IpEndpoint,
+        mtu: usize,
+        now: Duration,
+    ) -> Option<Vec<u8>> {
+        self.identity.as_ref()?;
+
+        match noise::message_type(&message)? {
+            MessageType::HandshakeInitiation => {
+                let initiation = HandshakeInitiation::from_bytes(&message);
+                let _ = self.handle_initiation(&initiation, endpoint, now);
+                None
+            }
+            MessageType::HandshakeResponse => {
+                let response = HandshakeResponse::from_bytes(&message);
+                let _ = self.handle_response(&response, endpoint, mtu, now);
+                None
+            }
+            // Cookie replies are not supported (see the module documentation of `noise`).
+            MessageType::CookieReply => None,
+            MessageType::TransportData => self.handle_data(message, endpoint, now),
+        }
+    }
+
+    fn handle_initiation(
+        &mut self,
+        initiation: &HandshakeInitiation,
+        endpoint: IpEndpoint,
+        now: Duration,
+    ) -> Option<()> {
+        let received = noise::consume_initiation(self.identity.as_ref()?, initiation)?;
+        let index = self
+            .peers
+            .iter()
+            .position(|peer| peer.public_key == received.peer_public)?;
+        // The timestamp must increase, so a replayed initiation is rejected.
+        if received.timestamp <= self.peers[index].latest_timestamp {
+            return None;
+        }
+
+        let local_index = self.allocate_index()?;
+        let ephemeral_private = random_key()?;
+
+        let peer = &mut self.peers[index];
+        let timestamp = received.timestamp;
+        let remote_index = received.sender_index;
+        let (response, keys) = noise::create_response(
+            received,
+            &peer.preshared_key,
+            ephemeral_private,
+            local_index,
+        )?;
+
+        peer.latest_timestamp = timestamp;
+        peer.endpoint = Some(endpoint);
+        peer.rx_bytes += size_of::<HandshakeInitiation>() as u64;
+        self.transport.send(&endpoint, response.as_bytes());
+        peer.tx_bytes += size_of::<HandshakeResponse>() as u64;
+        peer.last_sent = Some(now);
+        peer.install_responded_session(Session::new(keys, local_index, remote_index, now, false));
+
+        Some(())
+    }
+
+    fn handle_response(
+        &mut self,
+        response: &HandshakeResponse,
+        endpoint: IpEndpoint,
+        mtu: usize,
+        now: Duration,
+    ) -> Option<()> {
+        let Self {
+            identity: Some(identity),
+            peers,
+            transport,
+            ..
+        } = self
+        else {
+            return None;
+        };
+
+        let peer = peers.iter_mut().find(|peer| {
+            peer.handshake
+                .as_ref()
+                .is_some_and(|handshake| handshake.local_index == response.receiver_index)
+        })?;
+        let handshake = peer.handshake.as_ref().unwrap();
+        let keys =
+            noise::consume_response(identity, &handshake.state, &peer.preshared_key, response)?;
+
+        let handshake = peer.handshake.take().unwrap();
+        peer.install_initiated_session(Session::new(
+            keys,
+            handshake.local_index,
+            response.sender_index,
+            now,
+            true,
+        ));
+        peer.endpoint = Some(endpoint);
+        peer.last_handshake_time = Some(RealTimeClock::get().read_time());
+        peer.rx_bytes += size_of::<HandshakeResponse>() as u64;
+
+        // The responder cannot send data until we confirm the session, so a keepalive is sent
+        // if there are no staged packets.
+        if peer.staged_packets.is_empty() {
+            send_data(transport, peer, &[], mtu, now);
+        }
+        while let Some(packet) = peer.staged_packets.pop_front() {
+            if !send_data(transport, peer, &packet, mtu, now) {
+                peer.staged_packets.push_front(packet);
+                break;
+            }
+        }
+
+        Some(())
+    }
+
+    fn handle_data(
+        &mut self,
+        mut message: Vec<u8>,
+        endpoint: IpEndpoint,
+        now: Duration,
+    ) -> Option<Vec<u8>> {
+        let header = TransportHeader::from_bytes(&message[..TRANSPORT_HEADER_LEN]);
+        let index = self
+            .peers
+            .iter()
+            .position(|peer| peer.owns_index(header.receiver_index))?;
+
+        let peer = &mut self.peers[index];
+        let session = peer.receiving_session(header.receiver_index)?;
+        if !session.can_recv(now) || !session.replay_filter.check(header.counter) {
+            return None;
+        }
+        let message_len = message.len();
+        let packet = noise::open_transport(
+            &session.keys.recv,
+            header.counter,
+            &mut message[TRANSPORT_HEADER_LEN..],
+        )?;
+        session.replay_filter.accept(header.counter);
+
+        if peer.confirm_next_session(header.receiver_index) {
+            peer.last_handshake_time = Some(RealTimeClock::get().read_time());
+        }
+        peer.endpoint = Some(endpoint);
+        peer.rx_bytes += message_len as u64;
+
+        // An empty packet is a keepalive.
+        if packet.is_empty() {
+            return None;
+        }
+
+        // Remove the padding, and check that the peer is allowed to send the packet.
+        let (src_addr, _, packet_len) = parse_ip_header(packet)?;
+        let packet = packet[..packet_len].to_vec();
+        if self.route(&src_addr) != Some(index) {
+            return None;
+        }
+        self.peers[index].last_data_received = Some(now);
+
+        Some(packet)
+    }
+
+    /// Handles the timers, which is done every [`TIMER_INTERVAL`].
+    fn handle_timers(&mut self, own_iface: &Iface, mtu: usize, now: Duration) {
+        // Bind the sockets for the addresses that are configured after the last check.
+        let _ = self.transport.refresh(own_iface);
+
+        if self.identity.is_none() {
+            return;
+        }
+
+        for index in 0..self.peers.len() {
+            let peer = &mut self.peers[index];
+            peer.expire_sessions(now);
+
+            if let Some(handshake) = peer.handshake.as_ref() {
+                if now >= handshake.started_at + REKEY_ATTEMPT_TIME {
+                    // Give up. The staged packets cannot be sent.
+                    peer.handshake = None;
+                    peer.staged_packets.clear();
+                } else if now >= handshake.sent_at + REKEY_TIMEOUT {
+                    self.initiate_handshake(index, now);
+                }
+            }
+
+            let peer = &self.peers[index];
+            let needs_passive_keepalive = peer.last_data_received.is_some_and(|received| {
+                now >= received + KEEPALIVE_TIMEOUT
+                    && peer.last_sent.is_none_or(|sent| sent < received)
+            });
+            let needs_persistent_keepalive = peer.persistent_keepalive_interval != 0
+                && peer.last_sent.is_none_or(|sent| {
+                    now >= sent + Duration::from_secs(peer.persistent_keepalive_interval as u64)
+                });
+            if !needs_passive_keepalive && !needs_persistent_keepalive {
+                continue;
+            }
+
+            if needs_persistent_keepalive && peer.needs_handshake(now) {
+                self.initiate_handshake(index, now);
+            }
+            send_data(&self.transport, &mut self.peers[index], &[], mtu, now);
+        }
+    }
+}
+
+/// Encrypts a packet and sends it to the peer.
+///
+/// This method returns `false` if the packet cannot be sent because there is no usable
+/// session or no known endpoint.
+fn send_data(
+    transport: &Transport,
+    peer: &mut Peer,
+    packet: &[u8],
+    mtu: usize,
+    now: Duration,
+) -> bool {
+    let Some(endpoint) = peer.endpoint else {
+        return false;
+    };
+    let Some(session) = peer.sending_session(now) else {
+        return false;
+    };
+
+    let counter = session.send_counter;
+    session.send_counter += 1;
+    let message = noise::seal_transport(
+        &session.keys.send,
+        session.remote_index,
+        counter,
+        packet,
+        mtu,
+    );
+
+    transport.send(&endpoint, &message);
+    peer.tx_bytes += message.len() as u64;
+    peer.last_sent = Some(now);
+
+    true
+}
+
+/// Parses the header of an IP packet.
+///
+/// On success, this method returns the source address, the destination address, and the
+/// total length of the packet.
+fn parse_ip_header(packet: &[u8]) -> Option<(IpAddress, IpAddress, usize)> {
+    match IpVersion::of_packet(packet).ok()? {
+        IpVersion::Ipv4 => {
+            let ipv4_packet = Ipv4Packet::new_checked(packet).ok()?;
+            Some((
+                IpAddress::Ipv4(ipv4_packet.src_addr()),
+                IpAddress::Ipv4(ipv4_packet.dst_addr()),
+                ipv4_packet.total_len() as usize,
+            ))
+        }
+        IpVersion::Ipv6 => {
+            let ipv6_packet = Ipv6Packet::new_checked(packet).ok()?;
+            Some((
+                IpAddress::Ipv6(ipv6_packet.src_addr()),
+                IpAddress::Ipv6(ipv6_packet.dst_addr()),
+                ipv6_packet.header_len() + ipv6_packet.payload_len() as usize,
+            ))
+        }
+    }
+}
+
+/// Clears the host bits of a CIDR, like Linux does for allowed IPs.
+fn canonicalize_cidr(cidr: IpCidr) -> IpCidr {
+    let prefix_len = cidr.prefix_len();
+
+    let addr = match cidr.address() {
+        IpAddress::Ipv4(addr) => {
+            let mask = u32::MAX.checked_shl(32 - prefix_len as u32).unwrap_or(0);
+            IpAddress::Ipv4((u32::from(addr) & mask).into())
+        }
+        IpAddress::Ipv6(addr) => {
+            let mask = u128::MAX.checked_shl(128 - prefix_len as u32).unwrap_or(0);
+            IpAddress::Ipv6((u128::from(addr) & mask).into())
+        }
+    };
+    IpCidr::new(addr, prefix_len)
+}
+
+fn random_key() -> Option<WireGuardKey> {
+    let mut key = [0u8; KEY_LEN];
+    getrandom(&mut key).ok()?;
+    Some(key)
+}
+
+/// Returns the current time as a TAI64N timestamp.
+///
+/// Like Linux, the nanoseconds are rounded down, so that the timestamp does not leak the
+/// precise time.
+///
+/// Reference: <https://cr.yp.to/libtai/tai64.html>.
+fn tai64n_now() -> Timestamp {
+    const TAI64_EPOCH: u64 = 0x400000000000000a;
+    const NANOS_GRANULARITY: u32 = 1 << 24;
+
+    let now = RealTimeClock::get().read_time();
+    let nanos = now.subsec_nanos() / NANOS_GRANULARITY * NANOS_GRANULARITY;
+
+    let mut timestamp = [0u8; TIMESTAMP_LEN];
+    timestamp[..8].copy_from_slice(&(TAI64_EPOCH + now.as_secs()).to_be_bytes());
+    timestamp[8..].copy_from_slice(&nanos.to_be_bytes());
+    timestamp
+}
@@ -0,0 +1,464 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The cryptographic part of the WireGuard protocol.
+//!
+//! The handshake follows the `Noise_IKpsk2` pattern, where the initiator knows the static public
+//! key of the responder in advance. A successful handshake derives a pair of transport keys, with
+//! which the data packets are encrypted by ChaCha20-Poly1305.
+//!
+//! Cookie replies, which protect the responder under load, are not supported. So `mac2` is
+//! always zeros, and the cookie reply messages received from the peer are ignored.
+//!
+//! Reference: <https://www.wireguard.com/papers/wireguard.pdf>, Section 5.4.
+
+use aster_crypto::{
+    api::{self, Aead},
+    blake2s::{self, Blake2s, Hmac},
+    chacha20poly1305, x25519,
+};
+use spin::Once;
+
+use crate::prelude::*;
+
+/// The length of the keys (i.e., public keys, private keys, preshared keys, and symmetric keys).
+pub(super) const KEY_LEN: usize = 32;
+/// The length of the AEAD tags.
+pub(super) const TAG_LEN: usize = chacha20poly1305::TAG_LEN;
+/// The length of TAI64N timestamps.
+pub(super) const TIMESTAMP_LEN: usize = 12;
+
+pub(super) type Key = [u8; KEY_LEN];
+pub(super) type Timestamp = [u8; TIMESTAMP_LEN];
+
+const CONSTRUCTION: &[u8] = b"Noise_IKpsk2_25519_ChaChaPoly_BLAKE2s";
+const IDENTIFIER: &[u8] = b"WireGuard v1 zx2c4 Jason@zx2c4.com";
+const LABEL_MAC1: &[u8] = b"mac1----";
+
+/// The types of WireGuard messages.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, TryFromInt, PartialEq, Eq)]
+pub(super) enum MessageType {
+    HandshakeInitiation = 1,
+    HandshakeResponse = 2,
+    CookieReply = 3,
+    TransportData = 4,
+}
+
+/// The handshake initiation message.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct HandshakeInitiation {
+    /// The message type along with three reserved zero bytes
+    pub(super) type_: u32,
+    pub(super) sender_index: u32,
+    pub(super) ephemeral: Key,
+    pub(super) encrypted_static: [u8; KEY_LEN + TAG_LEN],
+    pub(super) encrypted_timestamp: [u8; TIMESTAMP_LEN + TAG_LEN],
+    pub(super) mac1: [u8; MAC_LEN],
+    pub(super) mac2: [u8; MAC_LEN],
+}
+
+/// The handshake response message.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct HandshakeResponse {
+    /// The message type along with three reserved zero bytes
+    pub(super) type_: u32,
+    pub(super) sender_index: u32,
+    pub(super) receiver_index: u32,
+    pub(super) ephemeral: Key,
+    pub(super) encrypted_nothing: [u8; TAG_LEN],
+    pub(super) mac1: [u8; MAC_LEN],
+    pub(super) mac2: [u8; MAC_LEN],
+}
+
+/// The header of the transport data message, which is followed by the encrypted packet.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct TransportHeader {
+    /// The message type along with three reserved zero bytes
+    pub(super) type_: u32,
+    pub(super) receiver_index: u32,
+    pub(super) counter: u64,
+}
+
+pub(super) const TRANSPORT_HEADER_LEN: usize = size_of::<TransportHeader>();
+
+const MAC_LEN: usize = 16;
+
+/// Returns the type of a message, or `None` if the message is malformed.
+pub(super) fn message_type(message: &[u8]) -> Option<MessageType> {
+    let type_ = u32::from_le_bytes(message.get(..4)?.try_into().unwrap());
+    let type_ = MessageType::try_from(type_).ok()?;
+
+    let is_len_valid = match type_ {
+        MessageType::HandshakeInitiation => message.len() == size_of::<HandshakeInitiation>(),
+        MessageType::HandshakeResponse => message.len() == size_of::<HandshakeResponse>(),
+        MessageType::CookieReply => true,
+        MessageType::TransportData => message.len() >= TRANSPORT_HEADER_LEN + TAG_LEN,
+    };
+    is_len_valid.then_some(type_)
+}
+
+/// The static identity of a WireGuard iface.
+pub(super) struct StaticIdentity {
+    private_key: Key,
+    public_key: Key,
+    /// The key of `mac1` in the messages sent to us
+    mac1_key: Key,
+}
+
+impl StaticIdentity {
+    /// Creates an identity with `private_key`.
+    ///
+    /// Like Linux, the private key is clamped first, so [`Self::private_key`] may differ from
+    /// `private_key`.
+    pub(super) fn new(mut private_key: Key) -> Self {
+        private_key[0] &= 248;
+        private_key[31] &= 127;
+        private_key[31] |= 64;
+        let public_key = x25519::public_key(&private_key);
+
+        Self {
+            private_key,
+            public_key,
+            mac1_key: mac1_key(&public_key),
+        }
+    }
+
+    pub(super) fn private_key(&self) -> &Key {
+        &self.private_key
+    }
+
+    pub(super) fn public_key(&self) -> &Key {
+        &self.public_key
+    }
+}
+
+/// Computes the key of `mac1` in the messages sent to the owner of `public_key`.
+fn mac1_key(public_key: &Key) -> Key {
+    let mut state = Blake2s::new(KEY_LEN);
+    state.update(LABEL_MAC1);
+    state.update(public_key);
+
+    let mut key = [0u8; KEY_LEN];
+    state.finalize(&mut key);
+    key
+}
+
+/// The keys derived from a successful handshake.
+pub(super) struct TransportKeys {
+    pub(super) send: Key,
+    pub(super) recv: Key,
+}
+
+/// The state of the initiator while waiting for the handshake response.
+pub(super) struct InitiatorState {
+    state: SymmetricState,
+    ephemeral_private: Key,
+}
+
+/// A handshake initiation that has been authenticated.
+pub(super) struct ReceivedInitiation {
+    state: SymmetricState,
+    /// The static public key of the initiator
+    pub(super) peer_public: Key,
+    pub(super) timestamp: Timestamp,
+    pub(super) sender_index: u32,
+    ephemeral_public: Key,
+}
+
+/// Creates a handshake initiation to the peer whose static public key is `peer_public`.
+pub(super) fn create_initiation(
+    identity: &StaticIdentity,
+    peer_public: &Key,
+    ephemeral_private: Key,
+    timestamp: &Timestamp,
+    sender_index: u32,
+) -> Option<(HandshakeInitiation, InitiatorState)> {
+    let ephemeral_public = x25519::public_key(&ephemeral_private);
+
+    let mut state = SymmetricState::new(peer_public);
+    state.mix_key(&ephemeral_public);
+    state.mix_hash(&ephemeral_public);
+
+    let mut message = HandshakeInitiation::new_zeroed();
+    message.type_ = MessageType::HandshakeInitiation as u32;
+    message.sender_index = sender_index;
+    message.ephemeral = ephemeral_public;
+
+    let key = state.mix_dh(&ephemeral_private, peer_public)?;
+    state.encrypt_and_hash(&key, &identity.public_key, &mut message.encrypted_static);
+    let key = state.mix_dh(&identity.private_key, peer_public)?;
+    state.encrypt_and_hash(&key, timestamp, &mut message.encrypted_timestamp);
+
+    message.mac1 = compute_mac1(&mac1_key(peer_public), message.as_bytes());
+
+    Some((
+        message,
+        InitiatorState {
+            state,
+            ephemeral_private,
+        },
+    ))
+}
+
+/// Authenticates a handshake initiation sent to us.
+///
+/// The caller should then find the peer by [`ReceivedInitiation::peer_public`] and check the
+/// timestamp to prevent replay attacks.
+pub(super) fn consume_initiation(
+    identity: &StaticIdentity,
+    message: &HandshakeInitiation,
+) -> Option<ReceivedInitiation> {
+    if !verify_mac1(&identity.mac1_key, message.as_bytes()) {
+        return None;
+    }
+
+    let mut state = SymmetricState::new(&identity.public_key);
+    state.mix_key(&message.ephemeral);
+    state.mix_hash(&message.ephemeral);
+
+    let key = state.mix_dh(&identity.private_key, &message.ephemeral)?;
+    let mut peer_public = [0u8; KEY_LEN];
+    state.decrypt_and_hash(&key, &message.encrypted_static, &mut peer_public)?;
+    let key = state.mix_dh(&identity.private_key, &peer_public)?;
+    let mut timestamp = [0u8; TIMESTAMP_LEN];
+    state.decrypt_and_hash(&key, &message.encrypted_timestamp, &mut timestamp)?;
+
+    Some(ReceivedInitiation {
+        state,
+        peer_public,
+        timestamp,
+        sender_index: message.sender_index,
+        ephemeral_public: message.ephemeral,
+    })
+}
+
+/// Creates a handshake response to an authenticated initiation.
+pub(super) fn create_response(
+    initiation: ReceivedInitiation,
+    preshared_key: &Key,
+    ephemeral_private: Key,
+    sender_index: u32,
+) -> Option<(HandshakeResponse, TransportKeys)> {
+    let ReceivedInitiation {
+        mut state,
+        peer_public,
+        sender_index: receiver_index,
+        ephemeral_public: peer_ephemeral,
+        ..
+    } = initiation;
+    let ephemeral_public = x25519::public_key(&ephemeral_private);
+
+    let mut message = HandshakeResponse::new_zeroed();
+    message.type_ = MessageType::HandshakeResponse as u32;
+    message.sender_index = sender_index;
+    message.receiver_index = receiver_index;
+    message.ephemeral = ephemeral_public;
+
+    state.mix_key(&ephemeral_public);
+    state.mix_hash(&ephemeral_public);
+    state.mix_dh(&ephemeral_private, &peer_ephemeral)?;
+    state.mix_dh(&ephemeral_private, &peer_public)?;
+    let key = state.mix_psk(preshared_key);
+    state.encrypt_and_hash(&key, &[], &mut message.encrypted_nothing);
+
+    message.mac1 = compute_mac1(&mac1_key(&peer_public), message.as_bytes());
+
+    let [recv, send] = kdf(&state.chaining_key, &[]);
+    Some((message, TransportKeys { send, recv }))
+}
+
+/// Authenticates a handshake response to our initiation and derives the transport keys.
+pub(super) fn consume_response(
+    identity: &StaticIdentity,
+    initiator: &InitiatorState,
+    preshared_key: &Key,
+    message: &HandshakeResponse,
+) -> Option<TransportKeys> {
+    if !verify_mac1(&identity.mac1_key, message.as_bytes()) {
+        return None;
+    }
+
+    let mut state = initiator.state.clone();
+    state.mix_key(&message.ephemeral);
+    state.mix_hash(&message.ephemeral);
+    state.mix_dh(&initiator.ephemeral_private, &message.ephemeral)?;
+    state.mix_dh(&identity.private_key, &message.ephemeral)?;
+    let key = state.mix_psk(preshared_key);
+    state.decrypt_and_hash(&key, &message.encrypted_nothing, &mut [])?;
+
+    let [send, recv] = kdf(&state.chaining_key, &[]);
+    Some(TransportKeys { send, recv })
+}
+
+/// Builds a transport data message that carries `packet`.
+///
+/// Like Linux, the packet is padded with zeros to a multiple of 16 bytes, which hides its exact
+/// length, unless the padding makes it exceed `mtu`.
+pub(super) fn seal_transport(
+    key: &Key,
+    receiver_index: u32,
+    counter: u64,
+    packet: &[u8],
+    mtu: usize,
+) -> Vec<u8> {
+    let padded_len = packet.len().next_multiple_of(16).min(mtu.max(packet.len()));
+
+    let header = TransportHeader {
+        type_: MessageType::TransportData as u32,
+        receiver_index,
+        counter,
+    };
+    let mut message = Vec::with_capacity(TRANSPORT_HEADER_LEN + padded_len + TAG_LEN);
+    message.extend_from_slice(header.as_bytes());
+    message.extend_from_slice(packet);
+    message.resize(TRANSPORT_HEADER_LEN + padded_len, 0);
+
+    let mut tag = [0u8; TAG_LEN];
+    aead().seal(
+        key,
+        &transport_nonce(counter),
+        &[],
+        &mut message[TRANSPORT_HEADER_LEN..],
+        &mut tag,
+    );
+    message.extend_from_slice(&tag);
+    message
+}
+
+/// Authenticates and decrypts the payload of a transport data message in place.
+///
+/// This function returns the decrypted (and still padded) packet.
+pub(super) fn open_transport<'a>(
+    key: &Key,
+    counter: u64,
+    payload: &'a mut [u8],
+) -> Option<&'a [u8]> {
+    let (ciphertext, tag) = payload.split_at_mut(payload.len().checked_sub(TAG_LEN)?);
+    aead()
+        .open(key, &transport_nonce(counter), &[], ciphertext, tag)
+        .ok()?;
+
+    Some(ciphertext)
+}
+
+/// Returns the implementation of ChaCha20-Poly1305 in the crypto API.
+fn aead() -> &'static dyn Aead {
+    static AEAD: Once<Arc<dyn Aead>> = Once::new();
+
+    AEAD.call_once(|| api::find_aead("rfc7539(chacha20,poly1305)").unwrap())
+        .as_ref()
+}
+
+fn transport_nonce(counter: u64) -> [u8; chacha20poly1305::NONCE_LEN] {
+    let mut nonce = [0u8; chacha20poly1305::NONCE_LEN];
+    nonce[4..].copy_from_slice(&counter.to_le_bytes());
+    nonce
+}
+
+/// Computes `mac1` of a handshake message, which covers the bytes before `mac1`.
+fn compute_mac1(key: &Key, message: &[u8]) -> [u8; MAC_LEN] {
+    let covered_len = message.len() - 2 * MAC_LEN;
+    blake2s::keyed_hash(key, &message[..covered_len])
+}
+
+fn verify_mac1(key: &Key, message: &[u8]) -> bool {
+    let mac1_offset = message.len() - 2 * MAC_LEN;
+    let expected = compute_mac1(key, message);
+    aster_crypto::constant_time_eq(&expected, &message[mac1_offset..mac1_offset + MAC_LEN])
+}
+
+/// The chaining key and the hash of a handshake.
+#[derive(Clone)]
+struct SymmetricState {
+    chaining_key: Key,
+    hash: Key,
+}
+
+impl SymmetricState {
+    /// Initializes the state with the static public key of the responder.
+    fn new(responder_public: &Key) -> Self {
+        let chaining_key = blake2s::hash(CONSTRUCTION);
+
+        let mut state = Self {
+            chaining_key,
+            hash: chaining_key,
+        };
+        state.mix_hash(IDENTIFIER);
+        state.mix_hash(responder_public);
+        state
+    }
+
+    fn mix_hash(&mut self, data: &[u8]) {
+        let mut state = Blake2s::new(KEY_LEN);
+        state.update(&self.hash);
+        state.update(data);
+        state.finalize(&mut self.hash);
+    }
+
+    fn mix_key(&mut self, input: &[u8]) {
+        let [chaining_key] = kdf(&self.chaining_key, input);
+        self.chaining_key = chaining_key;
+    }
+
+    /// Mixes the result of a Diffie-Hellman operation into the chaining key.
+    ///
+    /// This method returns the derived key, or `None` if the result is all zeros, which means
+    /// that the public key is of a small order.
+    fn mix_dh(&mut self, private_key: &Key, public_key: &Key) -> Option<Key> {
+        let shared = x25519::x25519(private_key, public_key);
+        if aster_crypto::constant_time_eq(&shared, &[0u8; KEY_LEN]) {
+            return None;
+        }
+
+        let [chaining_key, key] = kdf(&self.chaining_key, &shared);
+        self.chaining_key = chaining_key;
+        Some(key)
+    }
+
+    fn mix_psk(&mut self, preshared_key: &Key) -> Key {
+        let [chaining_key, tau, key] = kdf(&self.chaining_key, preshared_key);
+        self.chaining_key = chaining_key;
+        self.mix_hash(&tau);
+        key
+    }
+
+    /// Encrypts `plaintext` into `out`, which contains the ciphertext and the tag.
+    fn encrypt_and_hash(&mut self, key: &Key, plaintext: &[u8], out: &mut [u8]) {
+        let (ciphertext, tag) = out.split_at_mut(plaintext.len());
+        ciphertext.copy_from_slice(plaintext);
+        aead().seal(key, &transport_nonce(0), &self.hash, ciphertext, tag);
+
+        self.mix_hash(out);
+    }
+
+    /// Decrypts `input`, which contains the ciphertext and the tag, into `plaintext`.
+    fn decrypt_and_hash(&mut self, key: &Key, input: &[u8], plaintext: &mut [u8]) -> Option<()> {
+        let (ciphertext, tag) = input.split_at(plaintext.len());
+        plaintext.copy_from_slice(ciphertext);
+        aead()
+            .open(key, &transport_nonce(0), &self.hash, plaintext, tag)
+            .ok()?;
+
+        self.mix_hash(input);
+        Some(())
+    }
+}
+
+/// Derives `N` keys from the chaining key and `input` with HKDF.
+fn kdf<const N: usize>(chaining_key: &Key, input: &[u8]) -> [Key; N] {
+    let secret = blake2s::hmac(chaining_key, input);
+
+    let mut keys = [[0u8; KEY_LEN]; N];
+    for i in 0..N {
+        let mut state = Hmac::new(&secret);
+        if i > 0 {
+            state.update(&keys[i - 1]);
+        }
+        state.update(&[i as u8 + 1]);
+        keys[i] = state.finalize();
+    }
+    keys
+}
@@ -0,0 +1,324 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! WireGuard peers.
+//!
+//! Each peer keeps up to three sessions (i.e., keypairs derived from handshakes), like Linux:
+//!  - The current session, which is used to send packets;
+//!  - The previous session, which is kept for packets that are still in flight;
+//!  - The next session, which is established when we respond to a handshake. It becomes the
+//!    current session once the initiator confirms it by sending a transport data message.
+
+use core::time::Duration;
+
+use aster_bigtcp::wire::{IpAddress, IpCidr, IpEndpoint};
+
+use super::{
+    noise::{InitiatorState, Key, Timestamp, TransportKeys, TIMESTAMP_LEN},
+    WireGuardKey,
+};
+use crate::prelude::*;
+
+/// A WireGuard peer.
+pub(super) struct Peer {
+    pub(super) public_key: WireGuardKey,
+    pub(super) preshared_key: Key,
+    pub(super) endpoint: Option<IpEndpoint>,
+    /// The interval of persistent keepalives, or zero if they are disabled
+    pub(super) persistent_keepalive_interval: u16,
+    pub(super) allowed_ips: Vec<IpCidr>,
+
+    /// The handshake that we have initiated, if any
+    pub(super) handshake: Option<Handshake>,
+    /// The greatest timestamp of the handshake initiations received from the peer
+    pub(super) latest_timestamp: Timestamp,
+    pub(super) current: Option<Session>,
+    pub(super) previous: Option<Session>,
+    pub(super) next: Option<Session>,
+    /// The packets that wait for a session
+    pub(super) staged_packets: VecDeque<Vec<u8>>,
+
+    /// The time of the last completed handshake, in the real time
+    pub(super) last_handshake_time: Option<Duration>,
+    /// The time when we last sent a message to the peer, in the monotonic time
+    pub(super) last_sent: Option<Duration>,
+    /// The time when we last received an authenticated data packet (other than keepalives)
+    /// from the peer, in the monotonic time
+    pub(super) last_data_received: Option<Duration>,
+    pub(super) rx_bytes: u64,
+    pub(super) tx_bytes: u64,
+}
+
+impl Peer {
+    pub(super) fn new(public_key: WireGuardKey) -> Self {
+        Self {
+            public_key,
+            preshared_key: [0; 32],
+            endpoint: None,
+            persistent_keepalive_interval: 0,
+            allowed_ips: Vec::new(),
+            handshake: None,
+            latest_timestamp: [0; TIMESTAMP_LEN],
+            current: None,
+            previous: None,
+            next: None,
+            staged_packets: VecDeque::new(),
+            last_handshake_time: None,
+            last_sent: None,
+            last_data_received: None,
+            rx_bytes: 0,
+            tx_bytes: 0,
+        }
+    }
+
+    /// Returns whether one of the sessions or the pending handshake uses `index` as our index.
+    pub(super) fn owns_index(&self, index: u32) -> bool {
+        self.sessions().any(|session| session.local_index == index)
+            || self
+                .handshake
+                .as_ref()
+                .is_some_and(|handshake| handshake.local_index == index)
+    }
+
+    fn sessions(&self) -> impl Iterator<Item = &Session> {
+        [&self.current, &self.previous, &self.next]
+            .into_iter()
+            .flatten()
+    }
+
+    /// Returns the session that can be used to send packets.
+    pub(super) fn sending_session(&mut self, now: Duration) -> Option<&mut Session> {
+        self.current
+            .as_mut()
+            .filter(|session| session.can_send(now))
+    }
+
+    /// Returns the session whose local index is `index` for receiving packets.
+    pub(super) fn receiving_session(&mut self, index: u32) -> Option<&mut Session> {
+        [&mut self.current, &mut self.previous, &mut self.next]
+            .into_iter()
+            .flatten()
+            .find(|session| session.local_index == index)
+    }
+
+    /// Installs a session established by a handshake that we initiated.
+    pub(super) fn install_initiated_session(&mut self, session: Session) {
+        // The next session (if any) has never been confirmed, so it is dropped.
+        self.next = None;
+        self.previous = self.current.replace(session);
+    }
+
+    /// Installs a session established by a handshake that we responded to.
+    pub(super) fn install_responded_session(&mut self, session: Session) {
+        self.next = Some(session);
+    }
+
+    /// Confirms the next session, which is called when a data packet is received with the
+    /// session whose local index is `index`.
+    ///
+    /// This method returns whether the next session is confirmed, i.e., whether a handshake
+    /// that we responded to has been completed.
+    pub(super) fn confirm_next_session(&mut self, index: u32) -> bool {
+        if !self
+            .next
+            .as_ref()
+            .is_some_and(|next| next.local_index == index)
+        {
+            return false;
+        }
+
+        self.previous = self.current.take();
+        self.current = self.next.take();
+        true
+    }
+
+    /// Removes all the sessions and the pending handshake.
+    pub(super) fn clear_sessions(&mut self) {
+        self.handshake = None;
+        self.current = None;
+        self.previous = None;
+        self.next = None;
+    }
+
+    /// Removes the sessions that have expired.
+    ///
+    /// Like Linux, all the keys are erased if no new session has been established for a long
+    /// time.
+    pub(super) fn expire_sessions(&mut self, now: Duration) {
+        for session in [&mut self.current, &mut self.previous, &mut self.next] {
+            if session
+                .as_ref()
+                .is_some_and(|session| now >= session.created_at + REJECT_AFTER_TIME * 3)
+            {
+                *session = None;
+            }
+        }
+    }
+
+    /// Returns whether a new handshake should be initiated before sending a packet.
+    pub(super) fn needs_handshake(&self, now: Duration) -> bool {
+        if self.handshake.is_some() {
+            return false;
+        }
+
+        match self.current.as_ref() {
+            None => true,
+            Some(session) => {
+                !session.can_send(now)
+                    || session.send_counter >= REKEY_AFTER_MESSAGES
+                    || (session.is_initiator && now >= session.created_at + REKEY_AFTER_TIME)
+            }
+        }
+    }
+
+    /// Returns the length of the longest prefix in the allowed IPs of the peer that contains
+    /// `addr`, or `None` if `addr` is not allowed.
+    pub(super) fn allowed_prefix_len(&self, addr: &IpAddress) -> Option<u8> {
+        self.allowed_ips
+            .iter()
+            .filter(|cidr| cidr.contains_addr(addr))
+            .map(|cidr| cidr.prefix_len())
+            .max()
+    }
+
+    /// Stages a packet until a session is established.
+    ///
+    /// Like Linux, only the most recent packets are kept.
+    pub(super) fn stage_packet(&mut self, packet: Vec<u8>) {
+        if self.staged_packets.len() >= MAX_STAGED_PACKETS {
+            self.staged_packets.pop_front();
+        }
+        self.staged_packets.push_back(packet);
+    }
+}
+
+/// The maximum number of staged packets of each peer.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/drivers/net/wireguard/device.h>.
+const MAX_STAGED_PACKETS: usize = 128;
+
+/// A handshake that we have initiated.
+pub(super) struct Handshake {
+    pub(super) state: InitiatorState,
+    pub(super) local_index: u32,
+    /// The time when the last initiation is sent
+    pub(super) sent_at: Duration,
+    /// The time when the first initiation is sent, which is kept across retransmissions
+    pub(super) started_at: Duration,
+}
+
+/// A session established by a handshake.
+pub(super) struct Session {
+    pub(super) keys: TransportKeys,
+    pub(super) local_index: u32,
+    pub(super) remote_index: u32,
+    pub(super) send_counter: u64,
+    pub(super) replay_filter: ReplayFilter,
+    pub(super) created_at: Duration,
+    /// Whether we initiated the handshake
+    pub(super) is_initiator: bool,
+}
+
+impl Session {
+    pub(super) fn new(
+        keys: TransportKeys,
+        local_index: u32,
+        remote_index: u32,
+        now: Duration,
+        is_initiator: bool,
+    ) -> Self {
+        Self {
+            keys,
+            local_index,
+            remote_index,
+            send_counter: 0,
+            replay_filter: ReplayFilter::new(),
+            created_at: now,
+            is_initiator,
+        }
+    }
+
+    fn can_send(&self, now: Duration) -> bool {
+        now < self.created_at + REJECT_AFTER_TIME && self.send_counter < REJECT_AFTER_MESSAGES
+    }
+
+    pub(super) fn can_recv(&self, now: Duration) -> bool {
+        now < self.created_at + REJECT_AFTER_TIME
+    }
+}
+
+/// A sliding window that rejects replayed counters.
+///
+/// Reference: <https://datatracker.ietf.org/doc/html/rfc6479>.
+pub(super) struct ReplayFilter {
+    /// The greatest counter that has been accepted
+    greatest: u64,
+    /// The bitmap of accepted counters, where each word covers 64 consecutive counters
+    bitmap: [u64; REPLAY_WINDOW_WORDS],
+}
+
+const REPLAY_WINDOW_WORDS: usize = 32;
+/// The number of counters below the greatest counter that can still be accepted.
+const REPLAY_WINDOW_SIZE: u64 = ((REPLAY_WINDOW_WORDS - 1) * 64) as u64;
+
+impl ReplayFilter {
+    fn new() -> Self {
+        Self {
+            greatest: 0,
+            bitmap: [0; REPLAY_WINDOW_WORDS],
+        }
+    }
+
+    /// Checks whether `counter` has not been accepted and is not too old.
+    pub(super) fn check(&self, counter: u64) -> bool {
+        if counter >= REJECT_AFTER_MESSAGES {
+            return false;
+        }
+        if counter > self.greatest {
+            return true;
+        }
+        if self.greatest - counter >= REPLAY_WINDOW_SIZE {
+            return false;
+        }
+
+        let (word, bit) = Self::position(counter);
+        self.bitmap[word] & bit == 0
+    }
+
+    /// Marks `counter` as accepted.
+    ///
+    /// This should only be called after [`Self::check`] succeeds and the packet is
+    /// authenticated.
+    pub(super) fn accept(&mut self, counter: u64) {
+        if counter > self.greatest {
+            let current_block = self.greatest / 64;
+            let new_block = counter / 64;
+            let num_cleared = (new_block - current_block).min(REPLAY_WINDOW_WORDS as u64);
+            for i in 1..=num_cleared {
+                self.bitmap[((current_block + i) % REPLAY_WINDOW_WORDS as u64) as usize] = 0;
+            }
+            self.greatest = counter;
+        }
+
+        let (word, bit) = Self::position(counter);
+        self.bitmap[word] |= bit;
+    }
+
+    fn position(counter: u64) -> (usize, u64) {
+        (
+            ((counter / 64) % REPLAY_WINDOW_WORDS as u64) as usize,
+            1 << (counter % 64),
+        )
+    }
+}
+
+// The protocol constants.
+//
+// Reference: <https://www.wireguard.com/papers/wireguard.pdf>, Section 6.1.
+
+pub(super) const REKEY_AFTER_MESSAGES: u64 = 1 << 60;
+pub(super) const REJECT_AFTER_MESSAGES: u64 = u64::MAX - (1 << 13);
+pub(super) const REKEY_AFTER_TIME: Duration = Duration::from_secs(120);
+pub(super) const REJECT_AFTER_TIME: Duration = Duration::from_secs(180);
+pub(super) const REKEY_ATTEMPT_TIME: Duration = Duration::from_secs(90);
+pub(super) const REKEY_TIMEOUT: Duration = Duration::from_secs(5);
+pub(super) const KEEPALIVE_TIMEOUT: Duration = Duration::from_secs(10);
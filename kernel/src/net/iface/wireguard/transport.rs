@@ -0,0 +1,171 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The UDP transport of WireGuard ifaces.
+//!
+//! In this network stack, UDP sockets are bound to the addresses of specific ifaces. So a
+//! WireGuard iface listens on its port with one socket for each iface that has an IPv4 address.
+//! Sockets for the ifaces that are configured later are bound the next time the sockets are
+//! refreshed (see [`Transport::refresh`]).
+//!
+//! Sending to or receiving from IPv6 endpoints is not supported yet.
+
+use aster_bigtcp::{
+    errors::udp::RecvError,
+    iface::BindPortConfig,
+    wire::{IpAddress, IpEndpoint},
+};
+
+use crate::{
+    net::{
+        iface::{all_ifaces, Iface, UdpSocket},
+        socket::ip::{datagram::DatagramObserver, get_ephemeral_iface},
+    },
+    prelude::*,
+    process::signal::Pollee,
+};
+
+/// The UDP sockets of a WireGuard iface.
+pub(super) struct Transport {
+    /// The port that the sockets are bound to, or zero if no socket has been bound
+    ///
+    /// If the user does not specify the port, an ephemeral port is allocated when the first
+    /// socket is bound.
+    port: u16,
+    sockets: Vec<UdpSocket>,
+    /// The pollee that is notified when the sockets receive datagrams
+    pollee: Pollee,
+}
+
+impl Transport {
+    pub(super) fn new(pollee: Pollee) -> Self {
+        Self {
+            port: 0,
+            sockets: Vec::new(),
+            pollee,
+        }
+    }
+
+    /// Returns the listening port.
+    pub(super) fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Closes all the sockets and listens on the new port, or on an ephemeral port if `port` is
+    /// zero.
+    pub(super) fn set_port(&mut self, port: u16, own_iface: &Iface) -> Result<()> {
+        let old_sockets = core::mem::take(&mut self.sockets);
+        let old_port = core::mem::replace(&mut self.port, port);
+        drop(old_sockets);
+
+        if let Err(err) = self.refresh(own_iface) {
+            // Go back to the old port. This may still fail if the old port has been used by
+            // others in the meantime, in which case we keep no sockets.
+            self.sockets.clear();
+            self.port = old_port;
+            let _ = self.refresh(own_iface);
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    /// Closes all the sockets.
+    pub(super) fn close(&mut self) {
+        self.sockets.clear();
+        self.port = 0;
+    }
+
+    /// Closes the sockets whose addresses are gone, and binds sockets for the new addresses.
+    ///
+    /// The iface `own_iface` is skipped, since WireGuard packets are never sent through the
+    /// WireGuard iface itself.
+    ///
+    /// This method fails only if the first socket cannot be bound. The ifaces whose port is
+    /// already in use are skipped.
+    pub(super) fn refresh(&mut self, own_iface: &Iface) -> Result<()> {
+        self.sockets.retain(|socket| {
+            let iface = socket.iface();
+            !iface.sched_poll().is_removed()
+                && iface.ipv4_addr().map(IpAddress::Ipv4) == Some(socket.local_endpoint().addr)
+        });
+
+        for iface in all_ifaces() {
+            if iface.index() == own_iface.index() {
+                continue;
+            }
+            let Some(ipv4_addr) = iface.ipv4_addr() else {
+                continue;
+            };
+            if self
+                .sockets
+                .iter()
+                .any(|socket| socket.iface().index() == iface.index())
+            {
+                continue;
+            }
+
+            match self.bind(&iface, IpAddress::Ipv4(ipv4_addr)) {
+                Ok(socket) => {
+                    self.port = socket.local_endpoint().port;
+                    self.sockets.push(socket);
+                }
+                Err(err) if self.sockets.is_empty() => return Err(err),
+                Err(_) => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn bind(&self, iface: &Arc<Iface>, addr: IpAddress) -> Result<UdpSocket> {
+        let bound_port = iface.bind(addr, BindPortConfig::new(self.port, false))?;
+
+        let observer = DatagramObserver::new(self.pollee.clone());
+        match UdpSocket::new_bind(bound_port, observer) {
+            Ok(socket) => Ok(socket),
+            Err((_, err)) => {
+                unreachable!("`new_bind fails with {:?}, which should not happen", err)
+            }
+        }
+    }
+
+    /// Sends a message to `endpoint`.
+    ///
+    /// Like other datagrams, the message is silently dropped if it cannot be sent.
+    pub(super) fn send(&self, endpoint: &IpEndpoint, message: &[u8]) {
+        let IpAddress::Ipv4(ipv4_addr) = endpoint.addr else {
+            return;
+        };
+
+        let iface = get_ephemeral_iface(&ipv4_addr);
+        let Some(socket) = self
+            .sockets
+            .iter()
+            .find(|socket| socket.iface().index() == iface.index())
+        else {
+            return;
+        };
+
+        let _ = socket.send(message.len(), *endpoint, |buffer| {
+            buffer.copy_from_slice(message)
+        });
+        socket.iface().poll();
+    }
+
+    /// Receives a message from one of the sockets.
+    ///
+    /// This method returns the message and the endpoint that sends the message.
+    pub(super) fn try_recv(&self) -> Option<(Vec<u8>, IpEndpoint)> {
+        self.pollee.invalidate();
+
+        self.sockets.iter().find_map(|socket| {
+            match socket.recv(|message, metadata| (message.to_vec(), metadata.endpoint)) {
+                Ok(result) => Some(result),
+                Err(RecvError::Exhausted) => None,
+                Err(RecvError::Truncated) => {
+                    unreachable!("`recv` should never fail with `RecvError::Truncated`")
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,404 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! A minimal DHCP client that obtains a lease during boot.
+//!
+//! The client runs on the link layer, since the ifaces have no IPv4 address yet. It broadcasts
+//! the requests on all candidate ifaces and takes the first lease that is acknowledged, like the
+//! DHCP client of Linux's `ipconfig`. The lease is never renewed.
+//!
+//! Reference: <https://datatracker.ietf.org/doc/html/rfc2131>.
+
+use core::time::Duration;
+
+use aster_bigtcp::{socket::LinkFilter, wire::Ipv4Address};
+use ostd::{sync::WaitQueue, timer::Jiffies};
+
+use super::Lease;
+use crate::{
+    net::{
+        iface::{Iface, LinkSocket},
+        socket::ip::datagram::DatagramObserver,
+    },
+    prelude::*,
+    process::signal::Pollee,
+    util::random::getrandom,
+    WaitTimeout,
+};
+
+const ETH_P_IP: u16 = 0x0800;
+const ETH_HEADER_LEN: usize = 14;
+const IPV4_HEADER_LEN: usize = 20;
+const UDP_HEADER_LEN: usize = 8;
+const IPPROTO_UDP: u8 = 17;
+
+const DHCP_SERVER_PORT: u16 = 67;
+const DHCP_CLIENT_PORT: u16 = 68;
+
+/// The length of the fixed part of a DHCP message, including the magic cookie.
+const DHCP_FIXED_LEN: usize = 240;
+const DHCP_MAGIC_COOKIE: [u8; 4] = [99, 130, 83, 99];
+/// The minimum length of a message, which some BOOTP relays and servers still expect.
+const BOOTP_MIN_LEN: usize = 300;
+
+const BOOTREQUEST: u8 = 1;
+const BOOTREPLY: u8 = 2;
+const HTYPE_ETHERNET: u8 = 1;
+/// Asks the servers to broadcast the replies, since the client cannot receive unicast IPv4
+/// packets before it has an address.
+const FLAG_BROADCAST: u16 = 0x8000;
+
+// The message types.
+const DHCPDISCOVER: u8 = 1;
+const DHCPOFFER: u8 = 2;
+const DHCPREQUEST: u8 = 3;
+const DHCPACK: u8 = 5;
+const DHCPNAK: u8 = 6;
+
+// The options.
+const OPT_PAD: u8 = 0;
+const OPT_SUBNET_MASK: u8 = 1;
+const OPT_ROUTER: u8 = 3;
+const OPT_DNS_SERVER: u8 = 6;
+const OPT_HOSTNAME: u8 = 12;
+const OPT_DOMAIN_NAME: u8 = 15;
+const OPT_ROOT_PATH: u8 = 17;
+const OPT_INTERFACE_MTU: u8 = 26;
+const OPT_REQUESTED_IP: u8 = 50;
+const OPT_LEASE_TIME: u8 = 51;
+const OPT_MESSAGE_TYPE: u8 = 53;
+const OPT_SERVER_ID: u8 = 54;
+const OPT_PARAMETER_LIST: u8 = 55;
+const OPT_MAX_MESSAGE_SIZE: u8 = 57;
+const OPT_CLIENT_ID: u8 = 61;
+const OPT_END: u8 = 255;
+
+/// The options whose values are requested from the servers.
+const REQUESTED_PARAMETERS: &[u8] = &[
+    OPT_SUBNET_MASK,
+    OPT_ROUTER,
+    OPT_DNS_SERVER,
+    OPT_HOSTNAME,
+    OPT_DOMAIN_NAME,
+    OPT_ROOT_PATH,
+    OPT_INTERFACE_MTU,
+];
+
+/// The timeout of the first attempt, which doubles with each retransmission.
+const INITIAL_TIMEOUT: Duration = Duration::from_secs(1);
+const MAX_TIMEOUT: Duration = Duration::from_secs(8);
+/// The number of transmissions before the client gives up.
+const MAX_ATTEMPTS: u32 = 6;
+/// The interval between two checks for replies.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Obtains a lease on one of `ifaces`.
+///
+/// Returns `None` if no server replies in time.
+pub(super) fn obtain_lease(ifaces: &[Arc<Iface>]) -> Option<(Arc<Iface>, Lease)> {
+    let mut clients: Vec<_> = ifaces.iter().filter_map(Client::new).collect();
+    if clients.is_empty() {
+        return None;
+    }
+
+    let start = Jiffies::elapsed().as_duration();
+    let sleep_queue = WaitQueue::new();
+
+    for attempt in 0..MAX_ATTEMPTS {
+        let timeout = (INITIAL_TIMEOUT * (1 << attempt)).min(MAX_TIMEOUT);
+        let secs = (Jiffies::elapsed().as_duration() - start).as_secs() as u16;
+        for client in clients.iter_mut() {
+            client.transmit(secs);
+        }
+
+        let deadline = Jiffies::elapsed().as_duration() + timeout;
+        while Jiffies::elapsed().as_duration() < deadline {
+            for client in clients.iter_mut() {
+                if let Some(lease) = client.process_replies(secs) {
+                    return Some((client.socket.iface().clone(), lease));
+                }
+            }
+            let _ = sleep_queue.wait_until_or_timeout(|| -> Option<()> { None }, &POLL_INTERVAL);
+        }
+    }
+
+    None
+}
+
+/// The state of a client.
+enum State {
+    /// Waiting for offers.
+    Selecting,
+    /// Waiting for the server to acknowledge an offer.
+    Requesting(Reply),
+}
+
+/// A DHCP client that runs on a single iface.
+struct Client {
+    socket: LinkSocket,
+    mac_addr: [u8; 6],
+    xid: u32,
+    state: State,
+}
+
+impl Client {
+    fn new(iface: &Arc<Iface>) -> Option<Self> {
+        use aster_bigtcp::wire::HardwareAddress;
+
+        let HardwareAddress::Ethernet(mac_addr) = iface.hardware_addr() else {
+            return None;
+        };
+
+        let mut xid = [0u8; 4];
+        getrandom(&mut xid).ok()?;
+
+        let socket = LinkSocket::new(
+            iface.clone(),
+            LinkFilter::EtherType(ETH_P_IP),
+            DatagramObserver::new(Pollee::new()),
+        );
+
+        Some(Self {
+            socket,
+            mac_addr: mac_addr.0,
+            xid: u32::from_ne_bytes(xid),
+            state: State::Selecting,
+        })
+    }
+
+    /// Transmits the message of the current state.
+    fn transmit(&self, secs: u16) {
+        let message = match &self.state {
+            State::Selecting => self.build_message(DHCPDISCOVER, secs, None),
+            State::Requesting(offer) => self.build_message(DHCPREQUEST, secs, Some(offer)),
+        };
+        let frame = build_frame(&self.mac_addr, &message);
+
+        if let Err(err) = self.socket.send(frame) {
+            warn!(
+                "[IPConfig] {}: failed to send DHCP message: {:?}",
+                self.socket.iface().name(),
+                err
+            );
+        }
+        self.socket.iface().poll();
+    }
+
+    /// Handles the received replies, and returns the lease once it is acknowledged.
+    fn process_replies(&mut self, secs: u16) -> Option<Lease> {
+        self.socket.iface().poll();
+
+        while let Some(reply) = self
+            .socket
+            .recv(|frame, _| parse_frame(frame, self.xid, &self.mac_addr))
+        {
+            let Some(reply) = reply else {
+                continue;
+            };
+
+            match (&self.state, reply.message_type) {
+                (State::Selecting, DHCPOFFER) => {
+                    debug!(
+                        "[IPConfig] {}: got DHCP offer {} from {:?}",
+                        self.socket.iface().name(),
+                        reply.lease.addr,
+                        reply.server_id
+                    );
+                    self.state = State::Requesting(reply);
+                    self.transmit(secs);
+                }
+                (State::Requesting(offer), DHCPACK) if offer.server_id == reply.server_id => {
+                    return Some(reply.lease);
+                }
+                (State::Requesting(offer), DHCPNAK) if offer.server_id == reply.server_id => {
+                    warn!(
+                        "[IPConfig] {}: the DHCP server declines the offer, restarting",
+                        self.socket.iface().name()
+                    );
+                    self.state = State::Selecting;
+                    self.transmit(secs);
+                }
+                _ => (),
+            }
+        }
+
+        None
+    }
+
+    fn build_message(&self, message_type: u8, secs: u16, offer: Option<&Reply>) -> Vec<u8> {
+        let mut message = vec![0u8; DHCP_FIXED_LEN];
+        message[0] = BOOTREQUEST;
+        message[1] = HTYPE_ETHERNET;
+        message[2] = self.mac_addr.len() as u8;
+        message[4..8].copy_from_slice(&self.xid.to_be_bytes());
+        message[8..10].copy_from_slice(&secs.to_be_bytes());
+        message[10..12].copy_from_slice(&FLAG_BROADCAST.to_be_bytes());
+        message[28..34].copy_from_slice(&self.mac_addr);
+        message[236..240].copy_from_slice(&DHCP_MAGIC_COOKIE);
+
+        message.extend_from_slice(&[OPT_MESSAGE_TYPE, 1, message_type]);
+        message.extend_from_slice(&[OPT_CLIENT_ID, 7, HTYPE_ETHERNET]);
+        message.extend_from_slice(&self.mac_addr);
+        if let Some(offer) = offer {
+            message.extend_from_slice(&[OPT_REQUESTED_IP, 4]);
+            message.extend_from_slice(&offer.lease.addr.octets());
+            if let Some(server_id) = offer.server_id {
+                message.extend_from_slice(&[OPT_SERVER_ID, 4]);
+                message.extend_from_slice(&server_id.octets());
+            }
+        }
+        let max_size = (self.socket.iface().mtu() as u16).max(576);
+        message.extend_from_slice(&[OPT_MAX_MESSAGE_SIZE, 2]);
+        message.extend_from_slice(&max_size.to_be_bytes());
+        message.extend_from_slice(&[OPT_PARAMETER_LIST, REQUESTED_PARAMETERS.len() as u8]);
+        message.extend_from_slice(REQUESTED_PARAMETERS);
+        message.push(OPT_END);
+        if message.len() < BOOTP_MIN_LEN {
+            message.resize(BOOTP_MIN_LEN, OPT_PAD);
+        }
+
+        message
+    }
+}
+
+/// A DHCP reply that is sent to the client.
+struct Reply {
+    message_type: u8,
+    server_id: Option<Ipv4Address>,
+    lease: Lease,
+}
+
+/// Wraps a DHCP message in a broadcast Ethernet frame.
+fn build_frame(mac_addr: &[u8; 6], message: &[u8]) -> Vec<u8> {
+    let udp_len = UDP_HEADER_LEN + message.len();
+    let ip_len = IPV4_HEADER_LEN + udp_len;
+
+    let mut frame = Vec::with_capacity(ETH_HEADER_LEN + ip_len);
+
+    // The Ethernet header.
+    frame.extend_from_slice(&[0xff; 6]);
+    frame.extend_from_slice(mac_addr);
+    frame.extend_from_slice(&ETH_P_IP.to_be_bytes());
+
+    // The IPv4 header, from 0.0.0.0 to 255.255.255.255.
+    let ip_start = frame.len();
+    frame.extend_from_slice(&[0x45, 0]);
+    frame.extend_from_slice(&(ip_len as u16).to_be_bytes());
+    frame.extend_from_slice(&[0, 0, 0, 0, 64, IPPROTO_UDP, 0, 0]);
+    frame.extend_from_slice(&Ipv4Address::UNSPECIFIED.octets());
+    frame.extend_from_slice(&Ipv4Address::BROADCAST.octets());
+    let checksum = ipv4_checksum(&frame[ip_start..]);
+    frame[ip_start + 10..ip_start + 12].copy_from_slice(&checksum.to_be_bytes());
+
+    // The UDP header. A zero checksum means that the checksum is not computed.
+    frame.extend_from_slice(&DHCP_CLIENT_PORT.to_be_bytes());
+    frame.extend_from_slice(&DHCP_SERVER_PORT.to_be_bytes());
+    frame.extend_from_slice(&(udp_len as u16).to_be_bytes());
+    frame.extend_from_slice(&[0, 0]);
+
+    frame.extend_from_slice(message);
+    frame
+}
+
+fn ipv4_checksum(header: &[u8]) -> u16 {
+    let mut sum = header
+        .chunks(2)
+        .map(|word| u16::from_be_bytes([word[0], *word.get(1).unwrap_or(&0)]) as u32)
+        .sum::<u32>();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// Parses a frame, and returns the DHCP reply that it carries for the client.
+fn parse_frame(frame: &[u8], xid: u32, mac_addr: &[u8; 6]) -> Option<Reply> {
+    let ip = frame.get(ETH_HEADER_LEN..)?;
+    if frame[12..14] != ETH_P_IP.to_be_bytes() || ip.len() < IPV4_HEADER_LEN {
+        return None;
+    }
+
+    let header_len = ((ip[0] & 0xf) as usize) * 4;
+    let total_len = u16::from_be_bytes([ip[2], ip[3]]) as usize;
+    let is_fragment = u16::from_be_bytes([ip[6], ip[7]]) & 0x3fff != 0;
+    if ip[0] >> 4 != 4 || ip[9] != IPPROTO_UDP || is_fragment || total_len > ip.len() {
+        return None;
+    }
+
+    let udp = ip.get(header_len..total_len)?;
+    if udp.len() < UDP_HEADER_LEN || udp[2..4] != DHCP_CLIENT_PORT.to_be_bytes() {
+        return None;
+    }
+
+    parse_message(&udp[UDP_HEADER_LEN..], xid, mac_addr)
+}
+
+fn parse_message(message: &[u8], xid: u32, mac_addr: &[u8; 6]) -> Option<Reply> {
+    if message.len() < DHCP_FIXED_LEN
+        || message[0] != BOOTREPLY
+        || message[4..8] != xid.to_be_bytes()
+        || message[28..34] != *mac_addr
+        || message[236..240] != DHCP_MAGIC_COOKIE
+    {
+        return None;
+    }
+
+    let mut lease = Lease::new(read_addr(&message[16..20])?);
+    lease.next_server = read_addr(&message[20..24]).filter(|addr| !addr.is_unspecified());
+    let mut message_type = None;
+    let mut server_id = None;
+
+    let mut options = &message[DHCP_FIXED_LEN..];
+    while let Some((&code, rest)) = options.split_first() {
+        if code == OPT_END {
+            break;
+        }
+        if code == OPT_PAD {
+            options = rest;
+            continue;
+        }
+        let (&len, rest) = rest.split_first()?;
+        let value = rest.get(..len as usize)?;
+        options = &rest[len as usize..];
+
+        match code {
+            OPT_MESSAGE_TYPE => message_type = value.first().copied(),
+            OPT_SERVER_ID => server_id = read_addr(value),
+            OPT_SUBNET_MASK => {
+                lease.prefix_len = read_addr(value).map(|mask| mask.to_bits().leading_ones() as u8)
+            }
+            OPT_ROUTER => lease.gateway = read_addr(value),
+            OPT_DNS_SERVER => {
+                lease.dns_servers = value.chunks_exact(4).filter_map(read_addr).collect()
+            }
+            OPT_HOSTNAME => lease.hostname = read_string(value),
+            OPT_DOMAIN_NAME => lease.domain = read_string(value),
+            OPT_ROOT_PATH => lease.root_path = read_string(value),
+            OPT_INTERFACE_MTU if value.len() == 2 => {
+                lease.mtu = Some(u16::from_be_bytes([value[0], value[1]]))
+            }
+            OPT_LEASE_TIME if value.len() == 4 => {
+                lease.lease_time = Some(u32::from_be_bytes(value.try_into().unwrap()))
+            }
+            _ => (),
+        }
+    }
+
+    Some(Reply {
+        message_type: message_type?,
+        server_id,
+        lease,
+    })
+}
+
+fn read_addr(bytes: &[u8]) -> Option<Ipv4Address> {
+    let octets: [u8; 4] = bytes.get(..4)?.try_into().ok()?;
+    Some(Ipv4Address::from(octets))
+}
+
+fn read_string(bytes: &[u8]) -> Option<String> {
+    let bytes = bytes.split(|byte| *byte == 0).next()?;
+    core::str::from_utf8(bytes)
+        .ok()
+        .filter(|s| !s.is_empty())
+        .map(ToString::to_string)
+}
@@ -0,0 +1,335 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Automatic IP configuration of the boot ifaces.
+//!
+//! The configuration is specified by the `ip=` option on the kernel command line, which follows
+//! the syntax of Linux:
+//!
+//! ```text
+//! ip=<client-ip>:<server-ip>:<gw-ip>:<netmask>:<hostname>:<device>:<autoconf>
+//! ip=off|none|dhcp|on|any|both|bootp
+//! ```
+//!
+//! Empty fields take their default values. If the client address is given, the iface is
+//! configured statically. Otherwise, the address is obtained with DHCP, unless `<autoconf>` is
+//! `off` or `none`. The configuration is done before the init process runs, so that the root
+//! file system can be mounted over the network without a DHCP client in the initramfs.
+//!
+//! Reference: <https://www.kernel.org/doc/html/v6.4/admin-guide/nfs/nfsroot.html>.
+
+mod dhcp;
+
+use aster_bigtcp::{
+    iface::IfaceRoute,
+    wire::{HardwareAddress, Ipv4Address, Ipv4Cidr},
+};
+use spin::Once;
+
+use super::iface::{all_ifaces, find_iface_by_name, Iface};
+use crate::prelude::*;
+
+/// The configuration that has been applied to the boot iface.
+#[derive(Debug, Clone)]
+pub struct IpConfig {
+    /// The name of the configured iface.
+    pub iface_name: String,
+    pub addr: Ipv4Address,
+    pub prefix_len: u8,
+    pub gateway: Option<Ipv4Address>,
+    /// The server that is specified on the command line or offered by DHCP.
+    ///
+    /// This is the default server of the network root file system.
+    pub server: Option<Ipv4Address>,
+    pub hostname: Option<String>,
+    pub domain: Option<String>,
+    pub dns_servers: Vec<Ipv4Address>,
+    /// The path of the root file system on the server, if it is provided by DHCP.
+    pub root_path: Option<String>,
+}
+
+static IP_CONFIG: Once<IpConfig> = Once::new();
+
+/// Returns the configuration that has been applied during boot, if any.
+pub fn ip_config() -> Option<&'static IpConfig> {
+    IP_CONFIG.get()
+}
+
+/// Configures the boot iface as specified by the `ip=` option.
+///
+/// Failures are logged instead of being fatal, since the system may still boot without network.
+pub fn init(option: Option<&str>) {
+    let Some(option) = option else {
+        return;
+    };
+    let Some(spec) = Spec::parse(option) else {
+        warn!("[IPConfig] invalid option ip={}, skip for now", option);
+        return;
+    };
+
+    let config = match spec.method {
+        Method::Off => return,
+        Method::Static => configure_static(&spec),
+        Method::Dhcp => configure_dhcp(&spec),
+    };
+    let Some(config) = config else {
+        return;
+    };
+
+    info!(
+        "[IPConfig] complete: device={}, addr={}/{}, gw={:?}, host={:?}, domain={:?}, \
+         server={:?}, rootpath={:?}, dns={:?}",
+        config.iface_name,
+        config.addr,
+        config.prefix_len,
+        config.gateway,
+        config.hostname,
+        config.domain,
+        config.server,
+        config.root_path,
+        config.dns_servers
+    );
+    IP_CONFIG.call_once(|| config);
+}
+
+/// The way to obtain the configuration.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum Method {
+    Off,
+    Static,
+    #[default]
+    Dhcp,
+}
+
+/// The parsed `ip=` option.
+#[derive(Debug, Default)]
+struct Spec {
+    client: Option<Ipv4Address>,
+    server: Option<Ipv4Address>,
+    gateway: Option<Ipv4Address>,
+    netmask: Option<Ipv4Address>,
+    hostname: Option<String>,
+    device: Option<String>,
+    method: Method,
+}
+
+impl Spec {
+    fn parse(option: &str) -> Option<Self> {
+        let mut spec = Spec::default();
+
+        if !option.contains(':') {
+            if let Some(method) = parse_method(option) {
+                spec.method = method;
+                return Some(spec);
+            }
+        }
+
+        let parse_addr = |field: &str| -> Option<Option<Ipv4Address>> {
+            if field.is_empty() {
+                Some(None)
+            } else {
+                field.parse().ok().map(Some)
+            }
+        };
+        let parse_name = |field: &str| (!field.is_empty()).then(|| field.to_string());
+
+        let mut fields = option.split(':');
+        spec.client = parse_addr(fields.next().unwrap_or(""))?;
+        spec.server = parse_addr(fields.next().unwrap_or(""))?;
+        spec.gateway = parse_addr(fields.next().unwrap_or(""))?;
+        spec.netmask = parse_addr(fields.next().unwrap_or(""))?;
+        spec.hostname = parse_name(fields.next().unwrap_or(""));
+        spec.device = parse_name(fields.next().unwrap_or(""));
+        let autoconf = fields.next().unwrap_or("");
+        // The remaining fields (the DNS and NTP servers) are ignored.
+
+        spec.method = match parse_method(autoconf) {
+            _ if spec.client.is_some() => Method::Static,
+            Some(Method::Off) => Method::Off,
+            _ => Method::Dhcp,
+        };
+
+        Some(spec)
+    }
+
+    /// Returns the ifaces that the configuration may apply to.
+    fn candidate_ifaces(&self) -> Vec<Arc<Iface>> {
+        if let Some(device) = &self.device {
+            return find_iface_by_name(device).into_iter().collect();
+        }
+
+        all_ifaces()
+            .into_iter()
+            .filter(|iface| matches!(iface.hardware_addr(), HardwareAddress::Ethernet(_)))
+            .collect()
+    }
+}
+
+fn parse_method(autoconf: &str) -> Option<Method> {
+    match autoconf {
+        "off" | "none" => Some(Method::Off),
+        "" | "on" | "any" | "dhcp" | "both" | "bootp" => Some(Method::Dhcp),
+        _ => None,
+    }
+}
+
+fn configure_static(spec: &Spec) -> Option<IpConfig> {
+    let Some(iface) = spec.candidate_ifaces().into_iter().next() else {
+        warn!("[IPConfig] no suitable network device is found");
+        return None;
+    };
+
+    let addr = spec.client.unwrap();
+    let prefix_len = match spec.netmask {
+        Some(netmask) => netmask.to_bits().leading_ones() as u8,
+        None => default_prefix_len(addr),
+    };
+    apply(&iface, addr, prefix_len, spec.gateway)?;
+
+    Some(IpConfig {
+        iface_name: iface.name().to_string(),
+        addr,
+        prefix_len,
+        gateway: spec.gateway,
+        server: spec.server,
+        hostname: spec.hostname.clone(),
+        domain: None,
+        dns_servers: Vec::new(),
+        root_path: None,
+    })
+}
+
+fn configure_dhcp(spec: &Spec) -> Option<IpConfig> {
+    let ifaces = spec.candidate_ifaces();
+    if ifaces.is_empty() {
+        warn!("[IPConfig] no suitable network device is found");
+        return None;
+    }
+
+    info!("[IPConfig] sending DHCP requests");
+    let Some((iface, lease)) = dhcp::obtain_lease(&ifaces) else {
+        warn!("[IPConfig] no DHCP reply is received, giving up");
+        return None;
+    };
+
+    let prefix_len = match (lease.prefix_len, spec.netmask) {
+        (_, Some(netmask)) => netmask.to_bits().leading_ones() as u8,
+        (Some(prefix_len), None) => prefix_len,
+        (None, None) => default_prefix_len(lease.addr),
+    };
+    let gateway = spec.gateway.or(lease.gateway);
+    apply(&iface, lease.addr, prefix_len, gateway)?;
+
+    if let Some(mtu) = lease.mtu {
+        if mtu as usize != iface.mtu() {
+            debug!(
+                "[IPConfig] {}: ignore the MTU {} offered by DHCP",
+                iface.name(),
+                mtu
+            );
+        }
+    }
+    if let Some(lease_time) = lease.lease_time {
+        debug!(
+            "[IPConfig] {}: the lease expires in {} seconds and will not be renewed",
+            iface.name(),
+            lease_time
+        );
+    }
+
+    Some(IpConfig {
+        iface_name: iface.name().to_string(),
+        addr: lease.addr,
+        prefix_len,
+        gateway,
+        server: spec.server.or(lease.next_server),
+        hostname: spec.hostname.clone().or(lease.hostname),
+        domain: lease.domain,
+        dns_servers: lease.dns_servers,
+        root_path: lease.root_path,
+    })
+}
+
+/// Assigns the address to the iface, and adds the default route through the gateway.
+fn apply(
+    iface: &Arc<Iface>,
+    addr: Ipv4Address,
+    prefix_len: u8,
+    gateway: Option<Ipv4Address>,
+) -> Option<()> {
+    let cidr = Ipv4Cidr::new(addr, prefix_len);
+    if !iface.ipv4_cidrs().contains(&cidr) {
+        if let Err(err) = iface.add_ipv4_cidr(cidr) {
+            warn!(
+                "[IPConfig] {}: cannot assign the address {}: {:?}",
+                iface.name(),
+                cidr,
+                err
+            );
+            return None;
+        }
+    }
+
+    if let Some(gateway) = gateway {
+        let default = Ipv4Cidr::new(Ipv4Address::UNSPECIFIED, 0);
+        let has_default = iface
+            .ipv4_routes()
+            .iter()
+            .any(|route| route.cidr == default);
+        if !has_default {
+            if let Err(err) = iface.add_ipv4_route(IfaceRoute {
+                cidr: default,
+                gateway,
+            }) {
+                warn!(
+                    "[IPConfig] {}: cannot add the default route via {}: {:?}",
+                    iface.name(),
+                    gateway,
+                    err
+                );
+            }
+        }
+    }
+
+    Some(())
+}
+
+/// Returns the prefix length of the classful network that `addr` belongs to.
+fn default_prefix_len(addr: Ipv4Address) -> u8 {
+    match addr.octets()[0] {
+        0..=127 => 8,
+        128..=191 => 16,
+        _ => 24,
+    }
+}
+
+/// The configuration that is offered by a DHCP server.
+struct Lease {
+    addr: Ipv4Address,
+    prefix_len: Option<u8>,
+    gateway: Option<Ipv4Address>,
+    /// The server to use in the next step of the boot process (`siaddr`).
+    next_server: Option<Ipv4Address>,
+    dns_servers: Vec<Ipv4Address>,
+    hostname: Option<String>,
+    domain: Option<String>,
+    root_path: Option<String>,
+    mtu: Option<u16>,
+    lease_time: Option<u32>,
+}
+
+impl Lease {
+    fn new(addr: Ipv4Address) -> Self {
+        Self {
+            addr,
+            prefix_len: None,
+            gateway: None,
+            next_server: None,
+            dns_servers: Vec::new(),
+            hostname: None,
+            domain: None,
+            root_path: None,
+            mtu: None,
+            lease_time: None,
+        }
+    }
+}
@@ -1,6 +1,7 @@
 // SPDX-License-Identifier: MPL-2.0
 
 pub mod iface;
+pub mod ipconfig;
 pub mod socket;
 
 pub fn init() {
@@ -1,37 +1,104 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use aster_bigtcp::wire::{IpAddress, IpEndpoint, Ipv4Address};
+use aster_bigtcp::wire::{IpAddress, IpEndpoint, IpVersion, Ipv4Address, Ipv6Address};
 
 use crate::{net::socket::SocketAddr, prelude::*, return_errno_with_message};
 
-impl TryFrom<SocketAddr> for IpEndpoint {
-    type Error = Error;
+/// The address family of an IP socket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IpFamily {
+    /// IPv4 (`AF_INET`)
+    V4,
+    /// IPv6 (`AF_INET6`)
+    V6,
+}
+
+impl IpFamily {
+    /// Converts the socket address to a local endpoint to bind.
+    ///
+    /// For IPv6 sockets, an IPv4-mapped IPv6 address (i.e., `::ffff:a.b.c.d`) is converted to the
+    /// IPv4 address, unless `is_v6only` is true, in which case this method fails with `EINVAL`.
+    pub(super) fn local_endpoint_from(
+        self,
+        socket_addr: SocketAddr,
+        is_v6only: bool,
+    ) -> Result<IpEndpoint> {
+        let (endpoint, is_mapped) = self.endpoint_from(socket_addr)?;
+        if is_mapped && is_v6only {
+            return_errno_with_message!(
+                Errno::EINVAL,
+                "IPv4-mapped addresses cannot be bound to IPv6-only sockets"
+            );
+        }
+        Ok(endpoint)
+    }
 
-    fn try_from(value: SocketAddr) -> Result<Self> {
-        match value {
-            SocketAddr::IPv4(addr, port) => Ok(IpEndpoint::new(addr.into(), port)),
+    /// Converts the socket address to a remote endpoint to connect or send to.
+    ///
+    /// This is similar to [`Self::local_endpoint_from`], but fails with `ENETUNREACH` if
+    /// an IPv4-mapped IPv6 address is used for IPv6-only sockets.
+    pub(super) fn remote_endpoint_from(
+        self,
+        socket_addr: SocketAddr,
+        is_v6only: bool,
+    ) -> Result<IpEndpoint> {
+        let (endpoint, is_mapped) = self.endpoint_from(socket_addr)?;
+        if is_mapped && is_v6only {
+            return_errno_with_message!(
+                Errno::ENETUNREACH,
+                "IPv4 peers are unreachable from IPv6-only sockets"
+            );
+        }
+        Ok(endpoint)
+    }
+
+    /// Converts the socket address to an endpoint and returns whether the address is an
+    /// IPv4-mapped IPv6 address.
+    fn endpoint_from(self, socket_addr: SocketAddr) -> Result<(IpEndpoint, bool)> {
+        match (self, socket_addr) {
+            (IpFamily::V4, SocketAddr::IPv4(addr, port)) => {
+                Ok((IpEndpoint::new(IpAddress::Ipv4(addr), port), false))
+            }
+            (IpFamily::V6, SocketAddr::IPv6(addr, port)) => match addr.to_ipv4_mapped() {
+                Some(ipv4_addr) => Ok((IpEndpoint::new(IpAddress::Ipv4(ipv4_addr), port), true)),
+                None => Ok((IpEndpoint::new(IpAddress::Ipv6(addr), port), false)),
+            },
             _ => return_errno_with_message!(
                 Errno::EAFNOSUPPORT,
                 "the address is in an unsupported address family"
             ),
         }
     }
-}
 
-impl From<IpEndpoint> for SocketAddr {
-    fn from(endpoint: IpEndpoint) -> Self {
+    /// Converts the endpoint to a socket address.
+    ///
+    /// For IPv6 sockets, IPv4 endpoints are converted to IPv4-mapped IPv6 addresses.
+    pub(super) fn socket_addr_from(self, endpoint: IpEndpoint) -> SocketAddr {
         let port = endpoint.port;
-        match endpoint.addr {
-            IpAddress::Ipv4(addr) => SocketAddr::IPv4(addr, port),
-            // TODO: support IPv6
+        match (self, endpoint.addr) {
+            (IpFamily::V4, IpAddress::Ipv4(addr)) => SocketAddr::IPv4(addr, port),
+            (IpFamily::V6, IpAddress::Ipv4(addr)) => SocketAddr::IPv6(addr.to_ipv6_mapped(), port),
+            (_, IpAddress::Ipv6(addr)) => SocketAddr::IPv6(addr, port),
+        }
+    }
+
+    /// Returns a local endpoint, which indicates that the local endpoint is unspecified.
+    ///
+    /// According to the Linux man pages and the Linux implementation, `getsockname()` will _not_
+    /// fail even if the socket is unbound. Instead, it will return an unspecified socket address.
+    /// This unspecified endpoint helps with that.
+    pub(super) const fn unspecified_endpoint(self) -> IpEndpoint {
+        match self {
+            IpFamily::V4 => IpEndpoint::new(IpAddress::Ipv4(Ipv4Address::UNSPECIFIED), 0),
+            IpFamily::V6 => IpEndpoint::new(IpAddress::Ipv6(Ipv6Address::UNSPECIFIED), 0),
         }
     }
-}
 
-/// A local endpoint, which indicates that the local endpoint is unspecified.
-///
-/// According to the Linux man pages and the Linux implementation, `getsockname()` will _not_ fail
-/// even if the socket is unbound. Instead, it will return an unspecified socket address. This
-/// unspecified endpoint helps with that.
-pub(super) const UNSPECIFIED_LOCAL_ENDPOINT: IpEndpoint =
-    IpEndpoint::new(IpAddress::Ipv4(Ipv4Address::UNSPECIFIED), 0);
+    /// Returns the version of the IP packets that the socket sends and receives.
+    pub(super) const fn ip_version(self) -> IpVersion {
+        match self {
+            IpFamily::V4 => IpVersion::Ipv4,
+            IpFamily::V6 => IpVersion::Ipv6,
+        }
+    }
+}
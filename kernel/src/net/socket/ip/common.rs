@@ -3,35 +3,31 @@
 use aster_bigtcp::{
     errors::BindError,
     iface::BindPortConfig,
-    wire::{IpAddress, IpEndpoint},
+    wire::{IpAddress, IpEndpoint, Ipv4Address, Ipv6Address},
 };
 
 use crate::{
-    net::iface::{BoundPort, Iface, IFACES},
+    net::iface::{all_ifaces, BoundPort, Iface},
     prelude::*,
 };
 
 pub(super) fn get_iface_to_bind(ip_addr: &IpAddress) -> Option<Arc<Iface>> {
-    let ifaces = IFACES.get().unwrap();
-    let IpAddress::Ipv4(ipv4_addr) = ip_addr;
-    ifaces
-        .iter()
-        .find(|iface| {
-            if let Some(iface_ipv4_addr) = iface.ipv4_addr() {
-                iface_ipv4_addr == *ipv4_addr
-            } else {
-                false
-            }
-        })
-        .map(Clone::clone)
+    all_ifaces().into_iter().find(|iface| match ip_addr {
+        IpAddress::Ipv4(ipv4_addr) => iface.ipv4_addr() == Some(*ipv4_addr),
+        IpAddress::Ipv6(ipv6_addr) => iface
+            .ipv6_cidrs()
+            .iter()
+            .any(|cidr| cidr.address() == *ipv6_addr),
+    })
 }
 
 /// Get a suitable iface to deal with sendto/connect request if the socket is not bound to an iface.
 /// If the remote address is the same as that of some iface, we will use the iface.
-/// Otherwise, we will use a default interface.
-fn get_ephemeral_iface(remote_ip_addr: &IpAddress) -> Arc<Iface> {
-    let ifaces = IFACES.get().unwrap();
-    let IpAddress::Ipv4(remote_ipv4_addr) = remote_ip_addr;
+/// Otherwise, we will use the iface that is directly connected to the remote address or has a
+/// route to it, preferring the longest matching prefix. If no such iface exists, we will use a
+/// default interface.
+pub(crate) fn get_ephemeral_iface(remote_ipv4_addr: &Ipv4Address) -> Arc<Iface> {
+    let ifaces = all_ifaces();
     if let Some(iface) = ifaces.iter().find(|iface| {
         if let Some(iface_ipv4_addr) = iface.ipv4_addr() {
             iface_ipv4_addr == *remote_ipv4_addr
@@ -41,10 +37,55 @@ fn get_ephemeral_iface(remote_ip_addr: &IpAddress) -> Arc<Iface> {
     }) {
         return iface.clone();
     }
+
+    let longest_prefix_len = |iface: &Arc<Iface>| {
+        let connected = iface.ipv4_cidrs().into_iter();
+        let routed = iface.ipv4_routes().into_iter().map(|route| route.cidr);
+        connected
+            .chain(routed)
+            .filter(|cidr| cidr.contains_addr(remote_ipv4_addr))
+            .map(|cidr| cidr.prefix_len())
+            .max()
+    };
+    if let Some(iface) = ifaces
+        .iter()
+        .filter_map(|iface| Some((iface, longest_prefix_len(iface)?)))
+        // `max_by_key` returns the last maximum, so reverse the ifaces to prefer the first one.
+        .rev()
+        .max_by_key(|(_, prefix_len)| *prefix_len)
+        .map(|(iface, _)| iface)
+    {
+        return iface.clone();
+    }
+
     // FIXME: use the virtio-net as the default interface
     ifaces[0].clone()
 }
 
+/// Selects a local IPv6 address to deal with sendto/connect request if the socket is not bound.
+///
+/// This is a simplified version of the source address selection in RFC 6724. If the remote
+/// address is the same as some local address, we will use the address. Otherwise, we will use a
+/// non-loopback address with the same scope (i.e., link-local or global) as the remote address.
+///
+/// Reference: <https://datatracker.ietf.org/doc/html/rfc6724#section-5>.
+fn select_ipv6_source_addr(remote_ipv6_addr: &Ipv6Address) -> Option<Ipv6Address> {
+    let ifaces = all_ifaces();
+    let local_addrs = || {
+        ifaces
+            .iter()
+            .flat_map(|iface| iface.ipv6_cidrs())
+            .map(|cidr| cidr.address())
+    };
+
+    if local_addrs().any(|addr| addr == *remote_ipv6_addr) {
+        return Some(*remote_ipv6_addr);
+    }
+
+    let is_link_local = remote_ipv6_addr.is_unicast_link_local();
+    local_addrs().find(|addr| !addr.is_loopback() && addr.is_unicast_link_local() == is_link_local)
+}
+
 pub(super) fn bind_port(endpoint: &IpEndpoint, can_reuse: bool) -> Result<BoundPort> {
     let iface = match get_iface_to_bind(&endpoint.addr) {
         Some(iface) => iface,
@@ -58,7 +99,7 @@ pub(super) fn bind_port(endpoint: &IpEndpoint, can_reuse: bool) -> Result<BoundP
 
     let bind_port_config = BindPortConfig::new(endpoint.port, can_reuse);
 
-    Ok(iface.bind(bind_port_config)?)
+    Ok(iface.bind(endpoint.addr, bind_port_config)?)
 }
 
 impl From<BindError> for Error {
@@ -74,8 +115,21 @@ impl From<BindError> for Error {
     }
 }
 
-pub(super) fn get_ephemeral_endpoint(remote_endpoint: &IpEndpoint) -> IpEndpoint {
-    let iface = get_ephemeral_iface(&remote_endpoint.addr);
-    let ip_addr = iface.ipv4_addr().unwrap();
-    IpEndpoint::new(IpAddress::Ipv4(ip_addr), 0)
+pub(super) fn get_ephemeral_endpoint(remote_endpoint: &IpEndpoint) -> Result<IpEndpoint> {
+    let ip_addr = match &remote_endpoint.addr {
+        IpAddress::Ipv4(remote_ipv4_addr) => get_ephemeral_iface(remote_ipv4_addr)
+            .ipv4_addr()
+            .map(IpAddress::Ipv4),
+        IpAddress::Ipv6(remote_ipv6_addr) => {
+            select_ipv6_source_addr(remote_ipv6_addr).map(IpAddress::Ipv6)
+        }
+    };
+
+    let Some(ip_addr) = ip_addr else {
+        return_errno_with_message!(
+            Errno::ENETUNREACH,
+            "no local address can be used to reach the remote address"
+        );
+    };
+    Ok(IpEndpoint::new(ip_addr, 0))
 }
@@ -7,7 +7,7 @@ use ostd::sync::PreemptDisabled;
 use takeable::Takeable;
 
 use self::{bound::BoundDatagram, unbound::UnboundDatagram};
-use super::{common::get_ephemeral_endpoint, UNSPECIFIED_LOCAL_ENDPOINT};
+use super::{common::get_ephemeral_endpoint, options::Ipv6OptionSet, IpFamily};
 use crate::{
     events::IoEvents,
     match_sock_option_mut,
@@ -36,17 +36,20 @@ pub(in crate::net) use self::observer::DatagramObserver;
 #[derive(Debug, Clone)]
 struct OptionSet {
     socket: SocketOptionSet,
+    ipv6: Ipv6OptionSet,
     // TODO: UDP option set
 }
 
 impl OptionSet {
     fn new() -> Self {
         let socket = SocketOptionSet::new_udp();
-        OptionSet { socket }
+        let ipv6 = Ipv6OptionSet::new();
+        OptionSet { socket, ipv6 }
     }
 }
 
 pub struct DatagramSocket {
+    family: IpFamily,
     options: RwLock<OptionSet>,
     inner: RwLock<Takeable<Inner>, PreemptDisabled>,
     is_nonblocking: AtomicBool,
@@ -91,15 +94,19 @@ impl Inner {
             return Ok(bound_datagram);
         }
 
-        let endpoint = get_ephemeral_endpoint(remote_endpoint);
+        let endpoint = match get_ephemeral_endpoint(remote_endpoint) {
+            Ok(endpoint) => endpoint,
+            Err(err) => return Err((err, self)),
+        };
         self.bind(&endpoint, false, observer)
     }
 }
 
 impl DatagramSocket {
-    pub fn new(is_nonblocking: bool) -> Arc<Self> {
+    pub fn new(is_nonblocking: bool, family: IpFamily) -> Arc<Self> {
         let unbound_datagram = UnboundDatagram::new();
         Arc::new(Self {
+            family,
             inner: RwLock::new(Takeable::new(Inner::Unbound(unbound_datagram))),
             is_nonblocking: AtomicBool::new(is_nonblocking),
             pollee: Pollee::new(),
@@ -107,6 +114,10 @@ impl DatagramSocket {
         })
     }
 
+    fn is_v6only(&self) -> bool {
+        self.options.read().ipv6.v6only()
+    }
+
     fn try_bind_ephemeral(&self, remote_endpoint: &IpEndpoint) -> Result<()> {
         // Fast path
         if let Inner::Bound(_) = self.inner.read().as_ref() {
@@ -199,12 +210,10 @@ impl DatagramSocket {
             return_errno_with_message!(Errno::EAGAIN, "the socket is not bound");
         };
 
-        let recv_bytes = bound_datagram
-            .try_recv(writer, flags)
-            .map(|(recv_bytes, remote_endpoint)| (recv_bytes, remote_endpoint.into()))?;
+        let (recv_bytes, remote_endpoint) = bound_datagram.try_recv(writer, flags)?;
         self.pollee.invalidate();
 
-        Ok(recv_bytes)
+        Ok((recv_bytes, self.family.socket_addr_from(remote_endpoint)))
     }
 
     fn try_send(
@@ -255,7 +264,9 @@ impl SocketPrivate for DatagramSocket {
 
 impl Socket for DatagramSocket {
     fn bind(&self, socket_addr: SocketAddr) -> Result<()> {
-        let endpoint = socket_addr.try_into()?;
+        let endpoint = self
+            .family
+            .local_endpoint_from(socket_addr, self.is_v6only())?;
 
         let can_reuse = self.options.read().socket.reuse_addr();
         let mut inner = self.inner.write();
@@ -275,7 +286,9 @@ impl Socket for DatagramSocket {
     }
 
     fn connect(&self, socket_addr: SocketAddr) -> Result<()> {
-        let endpoint = socket_addr.try_into()?;
+        let endpoint = self
+            .family
+            .remote_endpoint_from(socket_addr, self.is_v6only())?;
 
         self.try_bind_ephemeral(&endpoint)?;
 
@@ -291,8 +304,12 @@ impl Socket for DatagramSocket {
     fn addr(&self) -> Result<SocketAddr> {
         let inner = self.inner.read();
         match inner.as_ref() {
-            Inner::Unbound(_) => Ok(UNSPECIFIED_LOCAL_ENDPOINT.into()),
-            Inner::Bound(bound_datagram) => Ok(bound_datagram.local_endpoint().into()),
+            Inner::Unbound(_) => Ok(self
+                .family
+                .socket_addr_from(self.family.unspecified_endpoint())),
+            Inner::Bound(bound_datagram) => Ok(self
+                .family
+                .socket_addr_from(bound_datagram.local_endpoint())),
         }
     }
 
@@ -305,7 +322,7 @@ impl Socket for DatagramSocket {
         };
 
         remote_endpoint
-            .map(|endpoint| (*endpoint).into())
+            .map(|endpoint| self.family.socket_addr_from(*endpoint))
             .ok_or_else(|| Error::with_message(Errno::ENOTCONN, "the socket is not connected"))
     }
 
@@ -322,15 +339,15 @@ impl Socket for DatagramSocket {
 
         let MessageHeader {
             addr,
-            control_message,
+            control_messages,
         } = message_header;
 
         let endpoint = match addr {
-            Some(addr) => Some(addr.try_into()?),
+            Some(addr) => Some(self.family.remote_endpoint_from(addr, self.is_v6only())?),
             None => None,
         };
 
-        if control_message.is_some() {
+        if !control_messages.is_empty() {
             // TODO: Support sending control message
             warn!("sending control message is not supported");
         }
@@ -352,9 +369,16 @@ impl Socket for DatagramSocket {
         let (received_bytes, peer_addr) =
             self.block_on(IoEvents::IN, || self.try_recv(writer, flags))?;
 
-        // TODO: Receive control message
+        // TODO: Receive other control messages
+        let control_messages = self
+            .options
+            .read()
+            .socket
+            .timestamp_message()
+            .into_iter()
+            .collect();
 
-        let message_header = MessageHeader::new(Some(peer_addr), None);
+        let message_header = MessageHeader::new(Some(peer_addr), control_messages);
 
         Ok((received_bytes, message_header))
     }
@@ -369,14 +393,33 @@ impl Socket for DatagramSocket {
             _ => ()
         });
 
-        self.options.read().socket.get_option(option)
+        let options = self.options.read();
+
+        // Deal with socket-level options
+        match options.socket.get_option(option) {
+            Err(err) if err.error() == Errno::ENOPROTOOPT => (),
+            res => return res,
+        }
+
+        // Deal with IPv6-level options
+        options.ipv6.get_option(option, self.family)
     }
 
     fn set_option(&self, option: &dyn SocketOption) -> Result<()> {
         let mut options = self.options.write();
         let mut inner = self.inner.write();
 
-        match options.socket.set_option(option, inner.as_mut()) {
+        // Deal with socket-level options
+        let result = match options.socket.set_option(option, inner.as_mut()) {
+            Err(err) if err.error() == Errno::ENOPROTOOPT && self.family == IpFamily::V6 => {
+                // Deal with IPv6-level options
+                let is_bound = matches!(inner.as_ref(), Inner::Bound(_));
+                options.ipv6.set_option(option, is_bound)
+            }
+            result => result,
+        };
+
+        match result {
             Err(e) => Err(e),
             Ok(need_iface_poll) => {
                 let iface_to_poll = need_iface_poll
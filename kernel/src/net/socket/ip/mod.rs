@@ -4,6 +4,8 @@ mod addr;
 mod common;
 pub mod datagram;
 pub mod options;
+pub mod raw;
 pub mod stream;
 
-use addr::UNSPECIFIED_LOCAL_ENDPOINT;
+pub use addr::IpFamily;
+pub(crate) use common::get_ephemeral_iface;
@@ -4,6 +4,7 @@ use core::num::NonZeroU8;
 
 use aster_bigtcp::socket::NeedIfacePoll;
 
+use super::IpFamily;
 use crate::{
     impl_socket_options, match_sock_option_mut, match_sock_option_ref,
     net::socket::options::SocketOption, prelude::*,
@@ -31,6 +32,17 @@ impl IpOptionSet {
         }
     }
 
+    /// Creates the default IP-level options for raw sockets.
+    ///
+    /// Like Linux, `IP_HDRINCL` is enabled by default for `IPPROTO_RAW` sockets.
+    pub(super) const fn new_raw(is_ipproto_raw: bool) -> Self {
+        Self {
+            tos: 0,
+            ttl: IpTtl(None),
+            hdrincl: is_ipproto_raw,
+        }
+    }
+
     pub(super) fn get_option(&self, option: &mut dyn SocketOption) -> Result<()> {
         match_sock_option_mut!(option, {
             ip_tos: Tos => {
@@ -84,6 +96,7 @@ impl_socket_options!(
     pub struct Tos(i32);
     pub struct Ttl(IpTtl);
     pub struct Hdrincl(bool);
+    pub struct V6Only(bool);
 );
 
 #[derive(Debug, Clone, Copy)]
@@ -103,6 +116,66 @@ impl IpTtl {
     }
 }
 
+/// IPv6-level socket options.
+#[derive(Debug, Clone, Copy, CopyGetters, Setters)]
+#[get_copy = "pub"]
+#[set = "pub"]
+pub(super) struct Ipv6OptionSet {
+    v6only: bool,
+}
+
+impl Ipv6OptionSet {
+    /// Creates the default IPv6-level options.
+    ///
+    /// Like Linux, IPv6 sockets can also communicate with IPv4 peers by default, as the default
+    /// value of `/proc/sys/net/ipv6/bindv6only` is zero.
+    pub(super) const fn new() -> Self {
+        Self { v6only: false }
+    }
+
+    /// Gets the IPv6-level option.
+    ///
+    /// `family` is the address family of the socket. Like Linux, getting IPv6-level options from
+    /// IPv4 sockets fails with `EOPNOTSUPP`.
+    pub(super) fn get_option(&self, option: &mut dyn SocketOption, family: IpFamily) -> Result<()> {
+        match_sock_option_mut!(option, {
+            ipv6_v6only: V6Only => {
+                if family != IpFamily::V6 {
+                    return_errno_with_message!(Errno::EOPNOTSUPP, "the socket is not an IPv6 socket");
+                }
+                let v6only = self.v6only();
+                ipv6_v6only.set(v6only);
+            },
+            _ => return_errno_with_message!(Errno::ENOPROTOOPT, "the socket option is unknown")
+        });
+
+        Ok(())
+    }
+
+    /// Sets the IPv6-level option.
+    ///
+    /// `is_bound` indicates whether the socket has been bound to a local address. Like Linux,
+    /// `IPV6_V6ONLY` cannot be changed once the socket is bound.
+    pub(super) fn set_option(
+        &mut self,
+        option: &dyn SocketOption,
+        is_bound: bool,
+    ) -> Result<NeedIfacePoll> {
+        match_sock_option_ref!(option, {
+            ipv6_v6only: V6Only => {
+                if is_bound {
+                    return_errno_with_message!(Errno::EINVAL, "the socket is already bound");
+                }
+                let v6only = ipv6_v6only.get().unwrap();
+                self.set_v6only(*v6only);
+            },
+            _ => return_errno_with_message!(Errno::ENOPROTOOPT, "the socket option to be set is unknown")
+        });
+
+        Ok(NeedIfacePoll::FALSE)
+    }
+}
+
 pub trait SetIpLevelOption {
     fn set_hdrincl(&self, _hdrincl: bool) -> Result<()>;
 }
@@ -0,0 +1,612 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use aster_bigtcp::{
+    errors::raw::SendError,
+    wire::{IpAddress, IpEndpoint, IpProtocol, IpRepr},
+};
+use ostd::sync::PreemptDisabled;
+
+use self::{
+    packet::{fill_icmp_checksum, parse_included_header, RecvHeader},
+    ping::{check_ping_group, is_echo_reply_to, prepare_echo_request, PingIdent},
+};
+use super::{
+    common::{get_ephemeral_endpoint, get_iface_to_bind},
+    datagram::DatagramObserver,
+    options::{IpOptionSet, SetIpLevelOption},
+    IpFamily,
+};
+use crate::{
+    events::IoEvents,
+    match_sock_option_mut,
+    net::{
+        iface::{all_ifaces, RawIpSocket},
+        socket::{
+            options::{Error as SocketError, SocketOption},
+            private::SocketPrivate,
+            util::{
+                options::{SetSocketLevelOption, SocketOptionSet},
+                send_recv_flags::SendRecvFlags,
+                socket_addr::SocketAddr,
+                MessageHeader,
+            },
+            Socket,
+        },
+    },
+    prelude::*,
+    process::{
+        credentials::capabilities::CapSet,
+        posix_thread::AsPosixThread,
+        signal::{PollHandle, Pollable, Pollee},
+    },
+    util::{net::Protocol, MultiRead, MultiWrite},
+};
+
+mod packet;
+mod ping;
+
+pub use self::ping::PING_GROUP_RANGE;
+
+#[derive(Debug, Clone)]
+struct OptionSet {
+    socket: SocketOptionSet,
+    ip: IpOptionSet,
+}
+
+impl OptionSet {
+    fn new(protocol: IpProtocol) -> Self {
+        let socket = SocketOptionSet::new_udp();
+        let ip = IpOptionSet::new_raw(u8::from(protocol) == IPPROTO_RAW);
+        OptionSet { socket, ip }
+    }
+}
+
+/// The kind of a [`RawSocket`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    /// A raw socket (`SOCK_RAW`), which sends and receives IP packets of a specific protocol.
+    Raw,
+    /// A ping socket (`SOCK_DGRAM` with `IPPROTO_ICMP` or `IPPROTO_ICMPV6`), which sends ICMP
+    /// echo requests and receives the matching ICMP echo replies.
+    Ping,
+}
+
+/// A raw IP socket or a ping socket.
+///
+/// Raw IP sockets are not bound to any ifaces. Instead, a socket is created on each iface to
+/// receive incoming packets, and the socket on the iface that owns the source address is used to
+/// send outgoing packets.
+pub struct RawSocket {
+    family: IpFamily,
+    kind: Kind,
+    protocol: IpProtocol,
+    options: RwLock<OptionSet>,
+    inner: RwLock<Inner, PreemptDisabled>,
+    sockets: Vec<RawIpSocket>,
+    is_nonblocking: AtomicBool,
+    pollee: Pollee,
+}
+
+struct Inner {
+    /// The local address specified by `bind`.
+    local_addr: Option<IpAddress>,
+    /// The remote address specified by `connect`.
+    remote_addr: Option<IpAddress>,
+    /// The identifier of echo requests, which is only used by ping sockets.
+    ident: Option<PingIdent>,
+}
+
+impl RawSocket {
+    /// Creates a raw socket (i.e., a `SOCK_RAW` socket).
+    ///
+    /// Like Linux, this method fails with `EPERM` if the current thread does not have
+    /// `CAP_NET_RAW`.
+    pub fn new_raw(
+        is_nonblocking: bool,
+        family: IpFamily,
+        protocol: Protocol,
+    ) -> Result<Arc<Self>> {
+        let protocol = match protocol {
+            Protocol::IPPROTO_IP => return_errno_with_message!(
+                Errno::EPROTONOSUPPORT,
+                "the protocol must be specified for raw sockets"
+            ),
+            // Protocols that do not fit in the protocol field of IP headers.
+            Protocol::IPPROTO_MPTCP => return_errno_with_message!(
+                Errno::EPROTONOSUPPORT,
+                "the protocol is not supported for raw sockets"
+            ),
+            protocol => protocol as u8,
+        };
+
+        let current = current_thread!();
+        let credentials = current.as_posix_thread().unwrap().credentials();
+        if !credentials.effective_capset().contains(CapSet::NET_RAW) {
+            return_errno_with_message!(Errno::EPERM, "`CAP_NET_RAW` is required");
+        }
+
+        Ok(Self::new(
+            is_nonblocking,
+            family,
+            Kind::Raw,
+            IpProtocol::from(protocol),
+        ))
+    }
+
+    /// Creates a ping socket (i.e., a `SOCK_DGRAM` socket with `IPPROTO_ICMP` or
+    /// `IPPROTO_ICMPV6`).
+    ///
+    /// Like Linux, this method fails with `EACCES` if the current thread is not in the groups
+    /// specified by [`PING_GROUP_RANGE`].
+    pub fn new_ping(is_nonblocking: bool, family: IpFamily) -> Result<Arc<Self>> {
+        check_ping_group()?;
+
+        let protocol = match family {
+            IpFamily::V4 => IpProtocol::Icmp,
+            IpFamily::V6 => IpProtocol::Icmpv6,
+        };
+        Ok(Self::new(is_nonblocking, family, Kind::Ping, protocol))
+    }
+
+    fn new(is_nonblocking: bool, family: IpFamily, kind: Kind, protocol: IpProtocol) -> Arc<Self> {
+        let ip_version = family.ip_version();
+
+        let pollee = Pollee::new();
+        let sockets = all_ifaces()
+            .iter()
+            .map(|iface| {
+                RawIpSocket::new(
+                    iface.clone(),
+                    ip_version,
+                    protocol,
+                    DatagramObserver::new(pollee.clone()),
+                )
+            })
+            .collect();
+
+        Arc::new(Self {
+            family,
+            kind,
+            protocol,
+            options: RwLock::new(OptionSet::new(protocol)),
+            inner: RwLock::new(Inner {
+                local_addr: None,
+                remote_addr: None,
+                ident: None,
+            }),
+            sockets,
+            is_nonblocking: AtomicBool::new(is_nonblocking),
+            pollee,
+        })
+    }
+
+    /// Allocates an ephemeral identifier if the ping socket does not have one.
+    fn alloc_ephemeral_ident(&self) -> Result<()> {
+        // Fast path
+        if self.inner.read().ident.is_some() {
+            return Ok(());
+        }
+
+        // Slow path
+        let mut inner = self.inner.write();
+        if inner.ident.is_none() {
+            inner.ident = Some(PingIdent::alloc(0)?);
+        }
+        Ok(())
+    }
+
+    fn try_recv(
+        &self,
+        writer: &mut dyn MultiWrite,
+        _flags: SendRecvFlags,
+    ) -> Result<(usize, SocketAddr)> {
+        let inner = self.inner.read();
+
+        let mut result = None;
+        'outer: for socket in self.sockets.iter() {
+            loop {
+                let accepted = socket.recv(|packet| {
+                    let (data, src_addr) = self.accept_packet(&inner, packet)?;
+                    Some((writer.write(&mut VmReader::from(data)), src_addr))
+                });
+                match accepted {
+                    // The receive queue is empty.
+                    None => break,
+                    // The packet is not for this socket.
+                    Some(None) => continue,
+                    Some(Some(accepted)) => {
+                        result = Some(accepted);
+                        break 'outer;
+                    }
+                }
+            }
+        }
+
+        drop(inner);
+        // Packets may be dropped even if no packets are accepted, so always invalidate the events.
+        self.pollee.invalidate();
+
+        let Some((copied_res, src_addr)) = result else {
+            return_errno_with_message!(Errno::EAGAIN, "the receive buffer is empty");
+        };
+        let socket_addr = self.family.socket_addr_from(IpEndpoint::new(src_addr, 0));
+        Ok((copied_res?, socket_addr))
+    }
+
+    /// Checks whether the received IP packet should be accepted by the socket.
+    ///
+    /// If so, this method returns the data to receive and the source address.
+    fn accept_packet<'a>(&self, inner: &Inner, packet: &'a [u8]) -> Option<(&'a [u8], IpAddress)> {
+        let header = RecvHeader::parse(packet)?;
+
+        if inner
+            .local_addr
+            .is_some_and(|local_addr| local_addr != header.dst_addr)
+            || inner
+                .remote_addr
+                .is_some_and(|remote_addr| remote_addr != header.src_addr)
+        {
+            return None;
+        }
+
+        let data = match (self.kind, self.family) {
+            // Like Linux, IPv4 raw sockets receive the IP header, but IPv6 raw sockets do not.
+            (Kind::Raw, IpFamily::V4) => packet,
+            (Kind::Raw, IpFamily::V6) => &packet[header.header_len..],
+            (Kind::Ping, _) => {
+                let message = &packet[header.header_len..];
+                let ident = inner.ident.as_ref()?.get();
+                if !is_echo_reply_to(self.family, message, ident) {
+                    return None;
+                }
+                message
+            }
+        };
+
+        Some((data, header.src_addr))
+    }
+
+    fn try_send(
+        &self,
+        reader: &mut dyn MultiRead,
+        remote: Option<IpAddress>,
+        _flags: SendRecvFlags,
+    ) -> Result<usize> {
+        let len = reader.sum_lens();
+        if len > MAX_MESSAGE_LEN {
+            return_errno_with_message!(Errno::EMSGSIZE, "the message is too large");
+        }
+
+        let mut bytes = vec![0u8; len];
+        reader.read(&mut VmWriter::from(bytes.as_mut_slice()))?;
+
+        let (is_hdrincl, hop_limit) = {
+            let options = self.options.read();
+            (options.ip.hdrincl(), options.ip.ttl().get())
+        };
+
+        if self.kind == Kind::Ping {
+            self.alloc_ephemeral_ident()?;
+        }
+
+        let inner = self.inner.read();
+
+        let Some(remote_addr) = remote.or(inner.remote_addr) else {
+            return_errno_with_message!(
+                Errno::EDESTADDRREQ,
+                "the destination address is not specified"
+            );
+        };
+        let select_local_addr = |remote_addr: IpAddress| match inner.local_addr {
+            Some(local_addr) => Ok(local_addr),
+            None => get_ephemeral_endpoint(&IpEndpoint::new(remote_addr, 0))
+                .map(|endpoint| endpoint.addr),
+        };
+
+        let (ip_repr, payload) = if is_hdrincl {
+            let (ip_repr, header_len) = parse_included_header(&bytes)?;
+            if ip_repr.version() != self.family.ip_version() {
+                return_errno_with_message!(Errno::EINVAL, "the IP version is invalid");
+            }
+
+            // Like Linux, the source address is filled in if it is unspecified.
+            let ip_repr = if ip_repr.src_addr().is_unspecified() {
+                IpRepr::new(
+                    select_local_addr(ip_repr.dst_addr())?,
+                    ip_repr.dst_addr(),
+                    ip_repr.next_header(),
+                    ip_repr.payload_len(),
+                    ip_repr.hop_limit(),
+                )
+            } else {
+                ip_repr
+            };
+
+            (ip_repr, bytes.split_off(header_len))
+        } else {
+            let local_addr = select_local_addr(remote_addr)?;
+
+            if let Some(ident) = inner.ident.as_ref() {
+                prepare_echo_request(self.family, &mut bytes, ident.get())?;
+            }
+
+            // Like Linux, the checksum of ICMPv6 messages is always computed by the kernel.
+            if self.protocol == IpProtocol::Icmpv6 {
+                if bytes.len() < ICMP_HEADER_LEN {
+                    return_errno_with_message!(Errno::EINVAL, "the ICMPv6 message is too short");
+                }
+                let (IpAddress::Ipv6(src_addr), IpAddress::Ipv6(dst_addr)) =
+                    (local_addr, remote_addr)
+                else {
+                    unreachable!("IPv6 sockets can only use IPv6 addresses");
+                };
+                fill_icmp_checksum(&mut bytes, Some((src_addr, dst_addr)));
+            }
+
+            let ip_repr = IpRepr::new(
+                local_addr,
+                remote_addr,
+                self.protocol,
+                bytes.len(),
+                hop_limit,
+            );
+            (ip_repr, bytes)
+        };
+
+        drop(inner);
+
+        let Some(iface) = get_iface_to_bind(&ip_repr.src_addr()) else {
+            return_errno_with_message!(
+                Errno::EADDRNOTAVAIL,
+                "the source address is not available from the local machine"
+            );
+        };
+        let socket = self
+            .sockets
+            .iter()
+            .find(|socket| Arc::ptr_eq(socket.iface(), &iface))
+            .unwrap();
+
+        match socket.send(ip_repr, payload) {
+            Ok(()) => (),
+            Err(SendError::Unaddressable) => {
+                return_errno_with_message!(Errno::EINVAL, "the destination address is invalid");
+            }
+            Err(SendError::BufferFull) => {
+                return_errno_with_message!(Errno::EAGAIN, "the send buffer is full");
+            }
+            Err(SendError::TooLarge) => {
+                return_errno_with_message!(Errno::EMSGSIZE, "the message is too large");
+            }
+        }
+
+        self.pollee.invalidate();
+        iface.poll();
+
+        Ok(len)
+    }
+
+    fn check_io_events(&self) -> IoEvents {
+        let mut events = IoEvents::empty();
+
+        if self.sockets.iter().any(|socket| socket.can_recv()) {
+            events |= IoEvents::IN;
+        }
+
+        if self.sockets.iter().all(|socket| socket.can_send()) {
+            events |= IoEvents::OUT;
+        }
+
+        events
+    }
+}
+
+impl Pollable for RawSocket {
+    fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
+        self.pollee
+            .poll_with(mask, poller, || self.check_io_events())
+    }
+}
+
+impl SocketPrivate for RawSocket {
+    fn is_nonblocking(&self) -> bool {
+        self.is_nonblocking.load(Ordering::Relaxed)
+    }
+
+    fn set_nonblocking(&self, is_nonblocking: bool) {
+        self.is_nonblocking.store(is_nonblocking, Ordering::Relaxed);
+    }
+}
+
+impl Socket for RawSocket {
+    fn bind(&self, socket_addr: SocketAddr) -> Result<()> {
+        let endpoint = self.family.local_endpoint_from(socket_addr, true)?;
+
+        let local_addr = if endpoint.addr.is_unspecified() {
+            None
+        } else if get_iface_to_bind(&endpoint.addr).is_some() {
+            Some(endpoint.addr)
+        } else {
+            return_errno_with_message!(
+                Errno::EADDRNOTAVAIL,
+                "the address is not available from the local machine"
+            );
+        };
+
+        let mut inner = self.inner.write();
+
+        // Like Linux, the port of a ping socket is the identifier of echo requests.
+        if self.kind == Kind::Ping {
+            if inner.ident.is_some() {
+                return_errno_with_message!(Errno::EINVAL, "the socket is already bound");
+            }
+            inner.ident = Some(PingIdent::alloc(endpoint.port)?);
+        }
+
+        inner.local_addr = local_addr;
+
+        Ok(())
+    }
+
+    fn connect(&self, socket_addr: SocketAddr) -> Result<()> {
+        let endpoint = self.family.remote_endpoint_from(socket_addr, true)?;
+
+        if self.kind == Kind::Ping {
+            self.alloc_ephemeral_ident()?;
+        }
+
+        self.inner.write().remote_addr = Some(endpoint.addr);
+
+        Ok(())
+    }
+
+    fn addr(&self) -> Result<SocketAddr> {
+        let inner = self.inner.read();
+
+        let local_addr = inner
+            .local_addr
+            .unwrap_or(self.family.unspecified_endpoint().addr);
+        let port = match self.kind {
+            // Like Linux, the port of a raw socket is its protocol number.
+            Kind::Raw => u8::from(self.protocol) as u16,
+            Kind::Ping => inner.ident.as_ref().map_or(0, PingIdent::get),
+        };
+
+        Ok(self
+            .family
+            .socket_addr_from(IpEndpoint::new(local_addr, port)))
+    }
+
+    fn peer_addr(&self) -> Result<SocketAddr> {
+        let inner = self.inner.read();
+
+        inner
+            .remote_addr
+            .map(|addr| self.family.socket_addr_from(IpEndpoint::new(addr, 0)))
+            .ok_or_else(|| Error::with_message(Errno::ENOTCONN, "the socket is not connected"))
+    }
+
+    fn sendmsg(
+        &self,
+        reader: &mut dyn MultiRead,
+        message_header: MessageHeader,
+        flags: SendRecvFlags,
+    ) -> Result<usize> {
+        // TODO: Deal with flags
+        if !flags.is_all_supported() {
+            warn!("unsupported flags: {:?}", flags);
+        }
+
+        let MessageHeader {
+            addr,
+            control_messages,
+        } = message_header;
+
+        let remote_addr = match addr {
+            Some(addr) => Some(self.family.remote_endpoint_from(addr, true)?.addr),
+            None => None,
+        };
+
+        if !control_messages.is_empty() {
+            // TODO: Support sending control message
+            warn!("sending control message is not supported");
+        }
+
+        // TODO: Block if the send buffer is full
+        self.try_send(reader, remote_addr, flags)
+    }
+
+    fn recvmsg(
+        &self,
+        writer: &mut dyn MultiWrite,
+        flags: SendRecvFlags,
+    ) -> Result<(usize, MessageHeader)> {
+        // TODO: Deal with flags
+        if !flags.is_all_supported() {
+            warn!("unsupported flags: {:?}", flags);
+        }
+
+        let (received_bytes, peer_addr) =
+            self.block_on(IoEvents::IN, || self.try_recv(writer, flags))?;
+
+        // TODO: Receive other control messages
+        let control_messages = self
+            .options
+            .read()
+            .socket
+            .timestamp_message()
+            .into_iter()
+            .collect();
+
+        let message_header = MessageHeader::new(Some(peer_addr), control_messages);
+
+        Ok((received_bytes, message_header))
+    }
+
+    fn get_option(&self, option: &mut dyn SocketOption) -> Result<()> {
+        match_sock_option_mut!(option, {
+            socket_errors: SocketError => {
+                // TODO: Support socket errors for raw sockets
+                socket_errors.set(None);
+                return Ok(());
+            },
+            _ => ()
+        });
+
+        let options = self.options.read();
+
+        // Deal with socket-level options
+        match options.socket.get_option(option) {
+            Err(err) if err.error() == Errno::ENOPROTOOPT => (),
+            res => return res,
+        }
+
+        // Deal with IP-level options
+        options.ip.get_option(option)
+    }
+
+    fn set_option(&self, option: &dyn SocketOption) -> Result<()> {
+        let mut options = self.options.write();
+        let mut kind = self.kind;
+
+        // Deal with socket-level options
+        match options.socket.set_option(option, &mut kind) {
+            Err(err) if err.error() == Errno::ENOPROTOOPT => {
+                // Deal with IP-level options
+                options.ip.set_option(option, &mut kind)?;
+            }
+            Err(err) => return Err(err),
+            Ok(_) => (),
+        }
+
+        // Raw sockets have no states in the iface that depend on the options, so there is no
+        // need to poll the iface.
+        Ok(())
+    }
+}
+
+impl SetSocketLevelOption for Kind {}
+
+impl SetIpLevelOption for Kind {
+    fn set_hdrincl(&self, _hdrincl: bool) -> Result<()> {
+        match self {
+            Kind::Raw => Ok(()),
+            Kind::Ping => return_errno_with_message!(
+                Errno::ENOPROTOOPT,
+                "IP_HDRINCL cannot be set on ping sockets"
+            ),
+        }
+    }
+}
+
+const IPPROTO_RAW: u8 = 255;
+
+/// The maximum length of a message that can be sent.
+///
+/// This is the maximum length of an IPv4 packet, since the length field has only 16 bits.
+const MAX_MESSAGE_LEN: usize = 0xffff;
+
+/// The length of the common header of ICMP messages (i.e., type, code, and checksum).
+const ICMP_HEADER_LEN: usize = 4;
@@ -0,0 +1,132 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use aster_bigtcp::wire::{
+    IpAddress, IpProtocol, IpRepr, IpVersion, Ipv4Packet, Ipv6Address, Ipv6Packet,
+};
+
+use crate::prelude::*;
+
+/// Types of ICMP messages that are used by ping sockets.
+pub(super) struct IcmpType;
+
+impl IcmpType {
+    pub(super) const V4_ECHO_REPLY: u8 = 0;
+    pub(super) const V4_ECHO_REQUEST: u8 = 8;
+    pub(super) const V6_ECHO_REQUEST: u8 = 128;
+    pub(super) const V6_ECHO_REPLY: u8 = 129;
+}
+
+/// The addresses and the header length of a received IP packet.
+pub(super) struct RecvHeader {
+    pub(super) src_addr: IpAddress,
+    pub(super) dst_addr: IpAddress,
+    pub(super) header_len: usize,
+}
+
+impl RecvHeader {
+    /// Parses the header of a received IP packet.
+    ///
+    /// The packet has already been checked by the iface, so this method never fails for the
+    /// packets delivered to raw sockets.
+    pub(super) fn parse(packet: &[u8]) -> Option<Self> {
+        match IpVersion::of_packet(packet).ok()? {
+            IpVersion::Ipv4 => {
+                let ipv4_packet = Ipv4Packet::new_checked(packet).ok()?;
+                Some(Self {
+                    src_addr: IpAddress::Ipv4(ipv4_packet.src_addr()),
+                    dst_addr: IpAddress::Ipv4(ipv4_packet.dst_addr()),
+                    header_len: ipv4_packet.header_len() as usize,
+                })
+            }
+            IpVersion::Ipv6 => {
+                let ipv6_packet = Ipv6Packet::new_checked(packet).ok()?;
+                Some(Self {
+                    src_addr: IpAddress::Ipv6(ipv6_packet.src_addr()),
+                    dst_addr: IpAddress::Ipv6(ipv6_packet.dst_addr()),
+                    header_len: ipv6_packet.header_len(),
+                })
+            }
+        }
+    }
+}
+
+/// Parses the IP header that is included in the data to send (see `IP_HDRINCL`).
+///
+/// On success, this method returns the header and the length of the header. Like Linux, the
+/// packet length in the header is ignored, and IP options are not supported.
+pub(super) fn parse_included_header(packet: &[u8]) -> Result<(IpRepr, usize)> {
+    let invalid = || Error::with_message(Errno::EINVAL, "the included IP header is invalid");
+
+    match IpVersion::of_packet(packet).map_err(|_| invalid())? {
+        IpVersion::Ipv4 => {
+            if packet.len() < MIN_IPV4_HEADER_LEN {
+                return Err(invalid());
+            }
+            let ipv4_packet = Ipv4Packet::new_unchecked(packet);
+            let header_len = ipv4_packet.header_len() as usize;
+            if header_len < MIN_IPV4_HEADER_LEN || header_len > packet.len() {
+                return Err(invalid());
+            }
+            let ip_repr = IpRepr::new(
+                IpAddress::Ipv4(ipv4_packet.src_addr()),
+                IpAddress::Ipv4(ipv4_packet.dst_addr()),
+                ipv4_packet.next_header(),
+                packet.len() - header_len,
+                ipv4_packet.hop_limit(),
+            );
+            Ok((ip_repr, header_len))
+        }
+        IpVersion::Ipv6 => {
+            if packet.len() < IPV6_HEADER_LEN {
+                return Err(invalid());
+            }
+            let ipv6_packet = Ipv6Packet::new_unchecked(packet);
+            let ip_repr = IpRepr::new(
+                IpAddress::Ipv6(ipv6_packet.src_addr()),
+                IpAddress::Ipv6(ipv6_packet.dst_addr()),
+                ipv6_packet.next_header(),
+                packet.len() - IPV6_HEADER_LEN,
+                ipv6_packet.hop_limit(),
+            );
+            Ok((ip_repr, IPV6_HEADER_LEN))
+        }
+    }
+}
+
+const MIN_IPV4_HEADER_LEN: usize = 20;
+const IPV6_HEADER_LEN: usize = 40;
+
+/// Fills in the checksum of the ICMP message.
+///
+/// For ICMPv6 messages, `ipv6_addrs` should contain the source and destination addresses, since
+/// the checksum also covers the IPv6 pseudo-header.
+pub(super) fn fill_icmp_checksum(
+    message: &mut [u8],
+    ipv6_addrs: Option<(Ipv6Address, Ipv6Address)>,
+) {
+    message[2..4].fill(0);
+
+    let mut sum = 0;
+    if let Some((src_addr, dst_addr)) = ipv6_addrs {
+        sum += sum_be_words(&src_addr.octets());
+        sum += sum_be_words(&dst_addr.octets());
+        sum += sum_be_words(&(message.len() as u32).to_be_bytes());
+        sum += u8::from(IpProtocol::Icmpv6) as u32;
+    }
+    sum += sum_be_words(message);
+
+    // Fold the carries, which cannot overflow since the message is at most 64 KiB.
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+
+    message[2..4].copy_from_slice(&(!(sum as u16)).to_be_bytes());
+}
+
+/// Sums the big-endian 16-bit words. The last byte is padded with zero if the length is odd.
+fn sum_be_words(bytes: &[u8]) -> u32 {
+    bytes
+        .chunks(2)
+        .map(|chunk| u16::from_be_bytes([chunk[0], chunk.get(1).copied().unwrap_or(0)]) as u32)
+        .sum()
+}
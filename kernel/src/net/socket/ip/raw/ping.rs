@@ -0,0 +1,133 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The helpers for ping sockets.
+//!
+//! A ping socket is a `SOCK_DGRAM` socket with the `IPPROTO_ICMP` (or `IPPROTO_ICMPV6`) protocol.
+//! It can only send ICMP echo requests and receive the matching ICMP echo replies, so it is
+//! available to unprivileged users, as long as they are in the groups specified by
+//! `/proc/sys/net/ipv4/ping_group_range`.
+//!
+//! Reference: <https://lwn.net/Articles/422330/>.
+
+use alloc::collections::btree_set::BTreeSet;
+use core::ops::RangeInclusive;
+
+use super::packet::{fill_icmp_checksum, IcmpType};
+use crate::{
+    net::socket::ip::IpFamily,
+    prelude::*,
+    process::{credentials::Gid, posix_thread::AsPosixThread},
+};
+
+/// The groups that are allowed to create ping sockets.
+///
+/// The default value in Linux is `1..=0` (i.e., no groups are allowed). However, most
+/// distributions (e.g., via systemd) allow all groups, so we follow them here.
+pub const PING_GROUP_RANGE: RangeInclusive<u32> = 0..=i32::MAX as u32;
+
+/// Checks whether the current thread is allowed to create ping sockets.
+pub(super) fn check_ping_group() -> Result<()> {
+    let current = current_thread!();
+    let credentials = current.as_posix_thread().unwrap().credentials();
+
+    let is_allowed = |gid: Gid| PING_GROUP_RANGE.contains(&u32::from(gid));
+    if is_allowed(credentials.egid()) || credentials.groups().iter().copied().any(is_allowed) {
+        return Ok(());
+    }
+
+    return_errno_with_message!(
+        Errno::EACCES,
+        "the group is not allowed to create ping sockets"
+    );
+}
+
+/// The identifiers of echo requests that are used by ping sockets.
+///
+/// Linux uses the identifier as the "port" of a ping socket, so two ping sockets cannot use the
+/// same identifier.
+static PING_IDENTS: SpinLock<BTreeSet<u16>> = SpinLock::new(BTreeSet::new());
+
+/// An identifier of echo requests that is exclusively used by a ping socket.
+///
+/// The identifier is released when the object is dropped.
+#[derive(Debug)]
+pub(super) struct PingIdent(u16);
+
+impl PingIdent {
+    /// Allocates the identifier.
+    ///
+    /// If `ident` is zero, an unused identifier will be allocated from the ephemeral range.
+    pub(super) fn alloc(ident: u16) -> Result<Self> {
+        let mut idents = PING_IDENTS.lock();
+
+        if ident != 0 {
+            if !idents.insert(ident) {
+                return_errno_with_message!(Errno::EADDRINUSE, "the identifier is already in use");
+            }
+            return Ok(Self(ident));
+        }
+
+        // Like Linux, we use the same range as the ephemeral ports.
+        let Some(ident) = (EPHEMERAL_START..=EPHEMERAL_END).find(|ident| !idents.contains(ident))
+        else {
+            return_errno_with_message!(Errno::EAGAIN, "no ephemeral identifier is available");
+        };
+        idents.insert(ident);
+
+        Ok(Self(ident))
+    }
+
+    pub(super) fn get(&self) -> u16 {
+        self.0
+    }
+}
+
+impl Drop for PingIdent {
+    fn drop(&mut self) {
+        PING_IDENTS.lock().remove(&self.0);
+    }
+}
+
+const EPHEMERAL_START: u16 = 32768;
+const EPHEMERAL_END: u16 = 60999;
+
+/// Prepares the ICMP message to be sent from a ping socket.
+///
+/// The message must be an echo request. Its identifier will be replaced with `ident`. For IPv4,
+/// the checksum is also computed here, while for IPv6, the checksum should be computed later
+/// because it covers the IPv6 pseudo-header.
+pub(super) fn prepare_echo_request(family: IpFamily, message: &mut [u8], ident: u16) -> Result<()> {
+    if message.len() < ECHO_HEADER_LEN {
+        return_errno_with_message!(Errno::EINVAL, "the ICMP message is too short");
+    }
+
+    let expected_type = match family {
+        IpFamily::V4 => IcmpType::V4_ECHO_REQUEST,
+        IpFamily::V6 => IcmpType::V6_ECHO_REQUEST,
+    };
+    if message[0] != expected_type || message[1] != 0 {
+        return_errno_with_message!(Errno::EINVAL, "the ICMP message is not an echo request");
+    }
+
+    message[4..6].copy_from_slice(&ident.to_be_bytes());
+    if family == IpFamily::V4 {
+        fill_icmp_checksum(message, None);
+    }
+
+    Ok(())
+}
+
+/// Checks whether the ICMP message received by a ping socket is an echo reply to the socket.
+pub(super) fn is_echo_reply_to(family: IpFamily, message: &[u8], ident: u16) -> bool {
+    let expected_type = match family {
+        IpFamily::V4 => IcmpType::V4_ECHO_REPLY,
+        IpFamily::V6 => IcmpType::V6_ECHO_REPLY,
+    };
+
+    message.len() >= ECHO_HEADER_LEN
+        && message[0] == expected_type
+        && message[4..6] == ident.to_be_bytes()
+}
+
+/// The length of the header of echo requests and echo replies.
+const ECHO_HEADER_LEN: usize = 8;
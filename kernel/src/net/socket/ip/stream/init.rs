@@ -1,9 +1,6 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use core::{
-    net::Ipv4Addr,
-    sync::atomic::{AtomicBool, Ordering},
-};
+use core::sync::atomic::{AtomicBool, Ordering};
 
 use aster_bigtcp::{socket::RawTcpOption, wire::IpEndpoint};
 
@@ -12,10 +9,7 @@ use crate::{
     events::IoEvents,
     net::{
         iface::BoundPort,
-        socket::{
-            ip::common::{bind_port, get_ephemeral_endpoint},
-            SocketAddr,
-        },
+        socket::ip::common::{bind_port, get_ephemeral_endpoint},
     },
     prelude::*,
 };
@@ -93,7 +87,10 @@ impl InitStream {
         let bound_port = if let Some(bound_port) = self.bound_port {
             bound_port
         } else {
-            let endpoint = get_ephemeral_endpoint(remote_endpoint);
+            let endpoint = match get_ephemeral_endpoint(remote_endpoint) {
+                Ok(endpoint) => endpoint,
+                Err(err) => return Err((err, self)),
+            };
             match bind_port(&endpoint, false) {
                 Ok(bound_port) => bound_port,
                 Err(err) => return Err((err, self)),
@@ -164,12 +161,7 @@ impl InitStream {
         }
     }
 
-    pub fn try_recv(&self) -> Result<(usize, SocketAddr)> {
-        // FIXME: Linux does not return addresses for `recvfrom` on connection-oriented sockets.
-        // This is a placeholder that has no Linux equivalent. (Note also that in this case
-        // `getpeeraddr` will simply fail with `ENOTCONN`).
-        const UNSPECIFIED_SOCKET_ADDR: SocketAddr = SocketAddr::IPv4(Ipv4Addr::UNSPECIFIED, 0);
-
+    pub fn try_recv(&self) -> Result<usize> {
         // Below are some magic checks to make our behavior identical to Linux.
 
         if self.is_connect_done {
@@ -180,7 +172,7 @@ impl InitStream {
             return Err(err);
         }
 
-        Ok((0, UNSPECIFIED_SOCKET_ADDR))
+        Ok(0)
     }
 
     pub fn try_send(&self) -> Result<usize> {
@@ -194,7 +186,7 @@ impl InitStream {
     pub fn local_endpoint(&self) -> Option<IpEndpoint> {
         self.bound_port
             .as_ref()
-            .map(|bound_port| bound_port.endpoint().unwrap())
+            .map(|bound_port| bound_port.endpoint())
     }
 
     pub(super) fn check_io_events(&self) -> IoEvents {
@@ -19,8 +19,8 @@ use takeable::Takeable;
 use util::{Retrans, TcpOptionSet};
 
 use super::{
-    options::{IpOptionSet, SetIpLevelOption},
-    UNSPECIFIED_LOCAL_ENDPOINT,
+    options::{IpOptionSet, Ipv6OptionSet, SetIpLevelOption},
+    IpFamily,
 };
 use crate::{
     events::IoEvents,
@@ -58,6 +58,7 @@ pub(in crate::net) use self::observer::StreamObserver;
 pub use self::util::CongestionControl;
 
 pub struct StreamSocket {
+    family: IpFamily,
     options: RwLock<OptionSet>,
     state: RwLock<Takeable<State>, PreemptDisabled>,
     is_nonblocking: AtomicBool,
@@ -79,6 +80,7 @@ enum State {
 struct OptionSet {
     socket: SocketOptionSet,
     ip: IpOptionSet,
+    ipv6: Ipv6OptionSet,
     tcp: TcpOptionSet,
 }
 
@@ -86,22 +88,32 @@ impl OptionSet {
     fn new() -> Self {
         let socket = SocketOptionSet::new_tcp();
         let ip = IpOptionSet::new_tcp();
+        let ipv6 = Ipv6OptionSet::new();
         let tcp = TcpOptionSet::new();
-        OptionSet { socket, ip, tcp }
+        OptionSet {
+            socket,
+            ip,
+            ipv6,
+            tcp,
+        }
     }
 
     fn raw(&self) -> RawTcpOption {
         RawTcpOption {
             keep_alive: self.socket.keep_alive().then_some(KEEPALIVE_INTERVAL),
             is_nagle_enabled: !self.tcp.no_delay(),
+            congestion_control: self.tcp.congestion().to_raw(),
+            recv_buf_len: self.socket.recv_buf() as usize,
+            send_buf_len: self.socket.send_buf() as usize,
         }
     }
 }
 
 impl StreamSocket {
-    pub fn new(is_nonblocking: bool) -> Arc<Self> {
+    pub fn new(is_nonblocking: bool, family: IpFamily) -> Arc<Self> {
         let init_stream = InitStream::new();
         Arc::new(Self {
+            family,
             options: RwLock::new(OptionSet::new()),
             state: RwLock::new(Takeable::new(State::Init(init_stream))),
             is_nonblocking: AtomicBool::new(is_nonblocking),
@@ -109,10 +121,17 @@ impl StreamSocket {
         })
     }
 
-    fn new_accepted(connected_stream: ConnectedStream) -> Arc<Self> {
+    fn new_accepted(
+        connected_stream: ConnectedStream,
+        family: IpFamily,
+        ipv6_options: Ipv6OptionSet,
+    ) -> Arc<Self> {
         let options = connected_stream.raw_with(|raw_tcp_socket| {
             let mut options = OptionSet::new();
 
+            // Like Linux, the IPv6-level options are inherited from the listening socket.
+            options.ipv6 = ipv6_options;
+
             if raw_tcp_socket.keep_alive().is_some() {
                 options.socket.set_keep_alive(true);
             }
@@ -130,6 +149,7 @@ impl StreamSocket {
         connected_stream.init_observer(StreamObserver::new(pollee.clone()));
 
         Arc::new(Self {
+            family,
             options: RwLock::new(options),
             state: RwLock::new(Takeable::new(State::Connected(connected_stream))),
             is_nonblocking: AtomicBool::new(false),
@@ -288,7 +308,12 @@ impl StreamSocket {
         }
     }
 
+    fn is_v6only(&self) -> bool {
+        self.options.read().ipv6.v6only()
+    }
+
     fn try_accept(&self) -> Result<(Arc<dyn FileLike>, SocketAddr)> {
+        let ipv6_options = self.options.read().ipv6;
         let state = self.read_updated_state();
 
         let State::Listen(listen_stream) = state.as_ref() else {
@@ -297,8 +322,11 @@ impl StreamSocket {
 
         let accepted = listen_stream.try_accept().map(|connected_stream| {
             let remote_endpoint = connected_stream.remote_endpoint();
-            let accepted_socket = Self::new_accepted(connected_stream);
-            (accepted_socket as _, remote_endpoint.into())
+            let accepted_socket = Self::new_accepted(connected_stream, self.family, ipv6_options);
+            (
+                accepted_socket as _,
+                self.family.socket_addr_from(remote_endpoint),
+            )
         });
         let iface_to_poll = listen_stream.iface().clone();
 
@@ -321,7 +349,14 @@ impl StreamSocket {
             State::Init(init_stream) => {
                 let result = init_stream.try_recv();
                 self.pollee.invalidate();
-                return result;
+
+                // FIXME: Linux does not return addresses for `recvfrom` on connection-oriented
+                // sockets. This is a placeholder that has no Linux equivalent. (Note also that in
+                // this case `getpeeraddr` will simply fail with `ENOTCONN`).
+                let unspecified_addr = self
+                    .family
+                    .socket_addr_from(self.family.unspecified_endpoint());
+                return result.map(|recv_bytes| (recv_bytes, unspecified_addr));
             }
             State::Listen(_) => {
                 return_errno_with_message!(Errno::ENOTCONN, "the socket is not connected")
@@ -344,7 +379,7 @@ impl StreamSocket {
             iface.poll();
         }
 
-        Ok((recv_bytes, remote_endpoint.into()))
+        Ok((recv_bytes, self.family.socket_addr_from(remote_endpoint)))
     }
 
     fn try_send(&self, reader: &mut dyn MultiRead, flags: SendRecvFlags) -> Result<usize> {
@@ -424,7 +459,9 @@ impl SocketPrivate for StreamSocket {
 
 impl Socket for StreamSocket {
     fn bind(&self, socket_addr: SocketAddr) -> Result<()> {
-        let endpoint = socket_addr.try_into()?;
+        let endpoint = self
+            .family
+            .local_endpoint_from(socket_addr, self.is_v6only())?;
 
         let can_reuse = self.options.read().socket.reuse_addr();
         let mut state = self.write_updated_state();
@@ -436,7 +473,9 @@ impl Socket for StreamSocket {
     }
 
     fn connect(&self, socket_addr: SocketAddr) -> Result<()> {
-        let remote_endpoint = socket_addr.try_into()?;
+        let remote_endpoint = self
+            .family
+            .remote_endpoint_from(socket_addr, self.is_v6only())?;
 
         if let Some(result) = self.start_connect(&remote_endpoint) {
             return result;
@@ -513,12 +552,12 @@ impl Socket for StreamSocket {
         let local_endpoint = match state.as_ref() {
             State::Init(init_stream) => init_stream
                 .local_endpoint()
-                .unwrap_or(UNSPECIFIED_LOCAL_ENDPOINT),
+                .unwrap_or(self.family.unspecified_endpoint()),
             State::Connecting(connecting_stream) => connecting_stream.local_endpoint(),
             State::Listen(listen_stream) => listen_stream.local_endpoint(),
             State::Connected(connected_stream) => connected_stream.local_endpoint(),
         };
-        Ok(local_endpoint.into())
+        Ok(self.family.socket_addr_from(local_endpoint))
     }
 
     fn peer_addr(&self) -> Result<SocketAddr> {
@@ -530,7 +569,7 @@ impl Socket for StreamSocket {
             State::Connecting(connecting_stream) => connecting_stream.remote_endpoint(),
             State::Connected(connected_stream) => connected_stream.remote_endpoint(),
         };
-        Ok(remote_endpoint.into())
+        Ok(self.family.socket_addr_from(remote_endpoint))
     }
 
     fn sendmsg(
@@ -545,14 +584,14 @@ impl Socket for StreamSocket {
         }
 
         let MessageHeader {
-            control_message, ..
+            control_messages, ..
         } = message_header;
 
         // According to the Linux man pages, `EISCONN` _may_ be returned when the destination
         // address is specified for a connection-mode socket. In practice, the destination address
         // is simply ignored. We follow the same behavior as the Linux implementation to ignore it.
 
-        if control_message.is_some() {
+        if !control_messages.is_empty() {
             // TODO: Support sending control message
             warn!("sending control message is not supported");
         }
@@ -574,11 +613,18 @@ impl Socket for StreamSocket {
 
         let (received_bytes, _) = self.block_on(IoEvents::IN, || self.try_recv(writer, flags))?;
 
-        // TODO: Receive control message
+        // TODO: Receive other control messages
+        let control_messages = self
+            .options
+            .read()
+            .socket
+            .timestamp_message()
+            .into_iter()
+            .collect();
 
         // According to <https://elixir.bootlin.com/linux/v6.0.9/source/net/ipv4/tcp.c#L2645>,
         // peer address is ignored for connected socket.
-        let message_header = MessageHeader::new(None, None);
+        let message_header = MessageHeader::new(None, control_messages);
 
         Ok((received_bytes, message_header))
     }
@@ -606,6 +652,12 @@ impl Socket for StreamSocket {
             res => return res,
         }
 
+        // Deal with IPv6-level options
+        match options.ipv6.get_option(option, self.family) {
+            Err(err) if err.error() == Errno::ENOPROTOOPT => (),
+            res => return res,
+        }
+
         // Deal with TCP-level options
         // FIXME: Here we only return the previously set values, without actually
         // asking the underlying sockets for the real, effective values.
@@ -674,8 +726,16 @@ impl Socket for StreamSocket {
                 // Deal with IP-level options
                 match options.ip.set_option(option, state.as_mut()) {
                     Err(err) if err.error() == Errno::ENOPROTOOPT => {
-                        // Deal with TCP-level options
-                        do_tcp_setsockopt(option, &mut options, state.as_mut())?
+                        // Deal with IPv6-level options
+                        match do_ipv6_setsockopt(option, self.family, &mut options, state.as_ref())
+                        {
+                            Err(err) if err.error() == Errno::ENOPROTOOPT => {
+                                // Deal with TCP-level options
+                                do_tcp_setsockopt(option, &mut options, state.as_mut())?
+                            }
+                            Err(err) => return Err(err),
+                            Ok(need_iface_poll) => need_iface_poll,
+                        }
                     }
                     Err(err) => return Err(err),
                     Ok(need_iface_poll) => need_iface_poll,
@@ -698,6 +758,23 @@ impl Socket for StreamSocket {
     }
 }
 
+fn do_ipv6_setsockopt(
+    option: &dyn SocketOption,
+    family: IpFamily,
+    options: &mut OptionSet,
+    state: &State,
+) -> Result<NeedIfacePoll> {
+    if family != IpFamily::V6 {
+        return_errno_with_message!(Errno::ENOPROTOOPT, "the socket option to be set is unknown");
+    }
+
+    let is_bound = match state {
+        State::Init(init_stream) => init_stream.local_endpoint().is_some(),
+        State::Connecting(_) | State::Connected(_) | State::Listen(_) => true,
+    };
+    options.ipv6.set_option(option, is_bound)
+}
+
 fn do_tcp_setsockopt(
     option: &dyn SocketOption,
     options: &mut OptionSet,
@@ -760,6 +837,7 @@ fn do_tcp_setsockopt(
         tcp_congestion: Congestion => {
             let congestion = tcp_congestion.get().unwrap();
             options.tcp.set_congestion(*congestion);
+            state.set_raw_option(|raw_socket: &dyn RawTcpSetOption| raw_socket.set_congestion_control(congestion.to_raw()));
         },
         tcp_user_timeout: UserTimeout => {
             let user_timeout = tcp_user_timeout.get().unwrap();
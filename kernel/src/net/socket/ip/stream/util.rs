@@ -1,6 +1,6 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use aster_bigtcp::time::Duration;
+use aster_bigtcp::{socket::RawCongestionControl, time::Duration};
 
 use crate::prelude::*;
 
@@ -33,7 +33,7 @@ impl TcpOptionSet {
             syn_cnt: DEFAULT_SYN_CNT,
             defer_accept: Retrans(0),
             window_clamp: DEFAULT_WINDOW_CLAMP,
-            congestion: CongestionControl::Reno,
+            congestion: CongestionControl::Cubic,
             user_timeout: 0,
             receive_inq: false,
         }
@@ -47,6 +47,9 @@ impl Default for TcpOptionSet {
 }
 
 /// Initial RTO value
+///
+/// These values are only used to convert between seconds and retransmits as Linux does. The
+/// actual RTO is estimated from the RTT by smoltcp (see `RawTcpOption` in `aster-bigtcp`).
 const TCP_TIMEOUT_INIT: Duration = Duration::from_secs(1);
 const TCP_RTO_MAX: Duration = Duration::from_secs(120);
 
@@ -103,6 +106,9 @@ impl Retrans {
     }
 }
 
+/// The congestion control algorithm (`TCP_CONGESTION`).
+///
+/// Like Linux, CUBIC is used by default.
 #[derive(Debug, Clone, Copy)]
 pub enum CongestionControl {
     Reno,
@@ -129,4 +135,11 @@ impl CongestionControl {
             Self::Cubic => Self::CUBIC,
         }
     }
+
+    pub(super) fn to_raw(self) -> RawCongestionControl {
+        match self {
+            Self::Reno => RawCongestionControl::Reno,
+            Self::Cubic => RawCongestionControl::Cubic,
+        }
+    }
 }
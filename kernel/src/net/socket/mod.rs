@@ -2,8 +2,14 @@
 
 use self::options::SocketOption;
 pub use self::util::{
-    options::LingerOption, send_recv_flags::SendRecvFlags, shutdown_cmd::SockShutdownCmd,
-    socket_addr::SocketAddr, MessageHeader,
+    options::{
+        LingerOption, DEFAULT_RECVBUF, DEFAULT_SENDBUF, MAX_RECVBUF, MAX_SENDBUF, MIN_RECVBUF,
+        MIN_SENDBUF,
+    },
+    send_recv_flags::SendRecvFlags,
+    shutdown_cmd::SockShutdownCmd,
+    socket_addr::SocketAddr,
+    ControlMessage, MessageHeader,
 };
 use crate::{
     fs::{
@@ -12,10 +18,13 @@ use crate::{
     },
     prelude::*,
     util::{MultiRead, MultiWrite},
+    vm::vmo::Vmo,
 };
 
 pub mod ip;
+pub mod netlink;
 pub mod options;
+pub mod packet;
 pub mod unix;
 mod util;
 pub mod vsock;
@@ -104,6 +113,11 @@ pub trait Socket: private::SocketPrivate + Send + Sync {
         return_errno_with_message!(Errno::EOPNOTSUPP, "setsockopt() is not supported");
     }
 
+    /// Gets the VMO that can be mapped to the user space by `mmap`.
+    fn mmap_vmo(&self) -> Result<Vmo> {
+        return_errno_with_message!(Errno::ENODEV, "mmap() is not supported");
+    }
+
     /// Sends a message on the socket.
     fn sendmsg(
         &self,
@@ -135,10 +149,7 @@ impl<T: Socket + 'static> FileLike for T {
         // TODO: Set correct flags
         self.sendmsg(
             reader,
-            MessageHeader {
-                addr: None,
-                control_message: None,
-            },
+            MessageHeader::new(None, Vec::new()),
             SendRecvFlags::empty(),
         )
     }
@@ -0,0 +1,111 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use crate::{net::socket::SocketAddr, prelude::*};
+
+/// A netlink socket address.
+///
+/// A netlink socket address consists of a port number and a set of multicast groups. The port
+/// number identifies a socket (or the kernel, if the number is zero), and it is called the _port
+/// ID_ or _PID_ in Linux (although it is not necessarily a process ID).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NetlinkSocketAddr {
+    port: u32,
+    groups: GroupIdSet,
+}
+
+impl NetlinkSocketAddr {
+    pub const fn new(port: u32, groups: GroupIdSet) -> Self {
+        Self { port, groups }
+    }
+
+    /// Returns the address of the kernel.
+    pub const fn new_kernel() -> Self {
+        Self::new(KERNEL_PORT, GroupIdSet::new_empty())
+    }
+
+    pub const fn port(&self) -> u32 {
+        self.port
+    }
+
+    pub const fn groups(&self) -> GroupIdSet {
+        self.groups
+    }
+}
+
+impl TryFrom<SocketAddr> for NetlinkSocketAddr {
+    type Error = Error;
+
+    fn try_from(value: SocketAddr) -> Result<Self> {
+        let SocketAddr::Netlink(netlink_addr) = value else {
+            return_errno_with_message!(Errno::EINVAL, "the address is not a netlink address");
+        };
+        Ok(netlink_addr)
+    }
+}
+
+impl From<NetlinkSocketAddr> for SocketAddr {
+    fn from(value: NetlinkSocketAddr) -> Self {
+        SocketAddr::Netlink(value)
+    }
+}
+
+/// The port number of the kernel.
+pub(super) const KERNEL_PORT: u32 = 0;
+
+/// A set of netlink multicast groups.
+///
+/// In a netlink socket address, multicast groups are represented as a bit mask, where the group
+/// `n` corresponds to the bit `n - 1`. So only the first 32 groups can be represented.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GroupIdSet(u32);
+
+impl GroupIdSet {
+    pub const fn new(groups: u32) -> Self {
+        Self(groups)
+    }
+
+    pub const fn new_empty() -> Self {
+        Self(0)
+    }
+
+    /// Returns the set that contains only the group `group_id`.
+    ///
+    /// This method returns `None` if the group cannot be represented by the bit mask.
+    pub const fn new_one(group_id: u32) -> Option<Self> {
+        if group_id == 0 || group_id > MAX_GROUPS {
+            return None;
+        }
+        Some(Self(1 << (group_id - 1)))
+    }
+
+    pub const fn as_u32(&self) -> u32 {
+        self.0
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns the set that contains only the first group in this set, if any.
+    pub const fn first(&self) -> Option<Self> {
+        if self.0 == 0 {
+            return None;
+        }
+        Some(Self(1 << self.0.trailing_zeros()))
+    }
+
+    pub const fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn add(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+
+    pub fn remove(&mut self, other: Self) {
+        self.0 &= !other.0;
+    }
+}
+
+/// The maximum number of multicast groups that can be represented in [`GroupIdSet`].
+pub(super) const MAX_GROUPS: u32 = u32::BITS;
@@ -0,0 +1,82 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The controller of generic netlink families (i.e., the `nlctrl` family).
+//!
+//! The user space resolves the ID of a family from its name with `CTRL_CMD_GETFAMILY`.
+
+use super::{
+    message::{CCtrlAttr, CCtrlCmd, CCtrlOpAttr, CGenlMsgHeader, GENL_ID_CTRL},
+    GenericFamily, ALL_FAMILIES,
+};
+use crate::{
+    net::socket::netlink::{
+        kernel::{Request, Response},
+        message::{attr::AttrList, SegmentFlags, SegmentWriter},
+    },
+    prelude::*,
+};
+
+pub(super) fn handle_request(request: &Request, cmd: u8) -> Result<Response> {
+    match CCtrlCmd::try_from(cmd) {
+        Ok(CCtrlCmd::GETFAMILY) => get_family(request),
+        _ => return_errno_with_message!(Errno::EOPNOTSUPP, "the command is not supported"),
+    }
+}
+
+fn get_family(request: &Request) -> Result<Response> {
+    if request.is_dump() {
+        let segments = ALL_FAMILIES
+            .iter()
+            .map(|family| {
+                let mut writer = request.reply_writer(GENL_ID_CTRL, SegmentFlags::MULTI);
+                write_family(&mut writer, family);
+                writer.finish()
+            })
+            .collect();
+        return Ok(Response::Dump(segments));
+    }
+
+    let (_, attrs) = request.parse_body::<CGenlMsgHeader>()?;
+    let attrs = AttrList::parse(attrs)?;
+
+    let family = if let Some(id) = attrs.get_u16(CCtrlAttr::FAMILY_ID as u16)? {
+        ALL_FAMILIES.iter().find(|family| family.id == id)
+    } else if let Some(name) = attrs.get_str(CCtrlAttr::FAMILY_NAME as u16)? {
+        ALL_FAMILIES.iter().find(|family| family.name == name)
+    } else {
+        return_errno_with_message!(Errno::EINVAL, "the family is not specified");
+    };
+    let Some(family) = family else {
+        return_errno_with_message!(Errno::ENOENT, "the family does not exist");
+    };
+
+    let mut writer = request.reply_writer(GENL_ID_CTRL, SegmentFlags::empty());
+    write_family(&mut writer, family);
+    Ok(Response::Reply(writer.finish()))
+}
+
+fn write_family(writer: &mut SegmentWriter, family: &GenericFamily) {
+    writer.write_body(&CGenlMsgHeader {
+        cmd: CCtrlCmd::NEWFAMILY as u8,
+        version: CTRL_VERSION,
+        _reserved: 0,
+    });
+
+    writer.write_attr_u16(CCtrlAttr::FAMILY_ID as u16, family.id);
+    writer.write_attr_str(CCtrlAttr::FAMILY_NAME as u16, family.name);
+    writer.write_attr_u32(CCtrlAttr::VERSION as u16, family.version as u32);
+    // No family has a family-specific header.
+    writer.write_attr_u32(CCtrlAttr::HDRSIZE as u16, 0);
+    writer.write_attr_u32(CCtrlAttr::MAXATTR as u16, family.max_attr);
+    writer.write_nested_attr(CCtrlAttr::OPS as u16, |writer| {
+        for (index, (cmd, flags)) in family.ops.iter().enumerate() {
+            writer.write_nested_attr(index as u16 + 1, |writer| {
+                writer.write_attr_u32(CCtrlOpAttr::ID as u16, *cmd as u32);
+                writer.write_attr_u32(CCtrlOpAttr::FLAGS as u16, *flags);
+            });
+        }
+    });
+}
+
+/// The version of the controller.
+const CTRL_VERSION: u8 = 2;
@@ -0,0 +1,298 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The message format of the `NETLINK_GENERIC` protocol.
+//!
+//! The segment type of a generic netlink segment is the ID of the family, and the body of the
+//! segment is [`CGenlMsgHeader`], which selects the command of the family. The attributes that
+//! follow depend on the family and the command.
+//!
+//! Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/genetlink.h>.
+
+use crate::prelude::*;
+
+/// The header of generic netlink segments (i.e., `struct genlmsghdr` in Linux).
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct CGenlMsgHeader {
+    pub(super) cmd: u8,
+    /// The version of the family
+    #[expect(dead_code)]
+    pub(super) version: u8,
+    pub(super) _reserved: u16,
+}
+
+/// The family ID of the controller, which resolves the IDs of other families.
+pub(super) const GENL_ID_CTRL: u16 = 0x10;
+
+/// The commands of the controller.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, TryFromInt, PartialEq, Eq)]
+#[expect(clippy::upper_case_acronyms)]
+pub(super) enum CCtrlCmd {
+    NEWFAMILY = 1,
+    DELFAMILY = 2,
+    GETFAMILY = 3,
+}
+
+/// The attributes of the controller.
+#[repr(u16)]
+#[derive(Debug, Clone, Copy, TryFromInt, PartialEq, Eq)]
+#[expect(non_camel_case_types)]
+#[expect(clippy::upper_case_acronyms)]
+pub(super) enum CCtrlAttr {
+    /// The family ID as a `u16`
+    FAMILY_ID = 1,
+    /// The family name as a string
+    FAMILY_NAME = 2,
+    /// The version of the family as a `u32`
+    VERSION = 3,
+    /// The length of the family-specific header as a `u32`
+    HDRSIZE = 4,
+    /// The maximum attribute type of the family as a `u32`
+    MAXATTR = 5,
+    /// The commands of the family as a nested array of [`CCtrlOpAttr`]
+    OPS = 6,
+}
+
+/// The attributes of each command in [`CCtrlAttr::OPS`].
+#[repr(u16)]
+#[derive(Debug, Clone, Copy, TryFromInt, PartialEq, Eq)]
+#[expect(clippy::upper_case_acronyms)]
+pub(super) enum CCtrlOpAttr {
+    /// The command as a `u32`
+    ID = 1,
+    /// The flags of the command (`GENL_*`) as a `u32`
+    FLAGS = 2,
+}
+
+/// The flags of commands.
+pub(super) const GENL_CMD_CAP_DO: u32 = 0x02;
+pub(super) const GENL_CMD_CAP_DUMP: u32 = 0x04;
+pub(super) const GENL_UNS_ADMIN_PERM: u32 = 0x10;
+
+/// The commands of the `wireguard` family.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/wireguard.h>.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, TryFromInt, PartialEq, Eq)]
+#[expect(non_camel_case_types)]
+#[expect(clippy::upper_case_acronyms)]
+pub(super) enum CWgCmd {
+    GET_DEVICE = 0,
+    SET_DEVICE = 1,
+}
+
+/// The attributes of WireGuard devices.
+#[repr(u16)]
+#[derive(Debug, Clone, Copy, TryFromInt, PartialEq, Eq)]
+#[expect(non_camel_case_types)]
+#[expect(clippy::upper_case_acronyms)]
+pub(super) enum CWgDeviceAttr {
+    /// The iface index as a `u32`
+    IFINDEX = 1,
+    /// The iface name as a string
+    IFNAME = 2,
+    /// The private key as 32 bytes
+    PRIVATE_KEY = 3,
+    /// The public key as 32 bytes
+    PUBLIC_KEY = 4,
+    /// The flags (`WGDEVICE_F_*`) as a `u32`
+    FLAGS = 5,
+    /// The listening port as a `u16`
+    LISTEN_PORT = 6,
+    /// The firewall mark of the UDP packets as a `u32`
+    FWMARK = 7,
+    /// The peers as a nested array of [`CWgPeerAttr`]
+    PEERS = 8,
+}
+
+pub(super) const WG_DEVICE_ATTR_MAX: u32 = CWgDeviceAttr::PEERS as u32;
+
+/// Removes all the peers before adding the peers in the request.
+pub(super) const WGDEVICE_F_REPLACE_PEERS: u32 = 1 << 0;
+
+/// The attributes of WireGuard peers.
+#[repr(u16)]
+#[derive(Debug, Clone, Copy, TryFromInt, PartialEq, Eq)]
+#[expect(non_camel_case_types)]
+#[expect(clippy::upper_case_acronyms)]
+pub(super) enum CWgPeerAttr {
+    /// The public key as 32 bytes
+    PUBLIC_KEY = 1,
+    /// The preshared key as 32 bytes
+    PRESHARED_KEY = 2,
+    /// The flags (`WGPEER_F_*`) as a `u32`
+    FLAGS = 3,
+    /// The endpoint as `struct sockaddr_in` or `struct sockaddr_in6`
+    ENDPOINT = 4,
+    /// The interval of persistent keepalives in seconds as a `u16`
+    PERSISTENT_KEEPALIVE_INTERVAL = 5,
+    /// The time of the last handshake as `struct __kernel_timespec`
+    LAST_HANDSHAKE_TIME = 6,
+    /// The number of received bytes as a `u64`
+    RX_BYTES = 7,
+    /// The number of sent bytes as a `u64`
+    TX_BYTES = 8,
+    /// The allowed IPs as a nested array of [`CWgAllowedIpAttr`]
+    ALLOWEDIPS = 9,
+    /// The protocol version as a `u32`
+    PROTOCOL_VERSION = 10,
+}
+
+/// Removes the peer.
+pub(super) const WGPEER_F_REMOVE_ME: u32 = 1 << 0;
+/// Removes all the allowed IPs before adding the allowed IPs in the request.
+pub(super) const WGPEER_F_REPLACE_ALLOWEDIPS: u32 = 1 << 1;
+/// Updates the peer only if it exists.
+pub(super) const WGPEER_F_UPDATE_ONLY: u32 = 1 << 2;
+
+/// The attributes of WireGuard allowed IPs.
+#[repr(u16)]
+#[derive(Debug, Clone, Copy, TryFromInt, PartialEq, Eq)]
+#[expect(non_camel_case_types)]
+#[expect(clippy::upper_case_acronyms)]
+pub(super) enum CWgAllowedIpAttr {
+    /// The address family (`AF_INET` or `AF_INET6`) as a `u16`
+    FAMILY = 1,
+    /// The address, whose length depends on the address family
+    IPADDR = 2,
+    /// The prefix length as a `u8`
+    CIDR_MASK = 3,
+}
+
+/// The time of the last handshake (i.e., `struct __kernel_timespec` in Linux).
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+#[expect(dead_code)]
+pub(super) struct CKernelTimespec {
+    pub(super) sec: i64,
+    pub(super) nsec: i64,
+}
+
+/// The commands of the `TASKSTATS` family.
+///
+/// Reference: <https://docs.kernel.org/accounting/taskstats-struct.html>.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, TryFromInt, PartialEq, Eq)]
+#[expect(clippy::upper_case_acronyms)]
+pub(super) enum CTaskstatsCmd {
+    GET = 1,
+    NEW = 2,
+}
+
+/// The attributes of `TASKSTATS_CMD_GET`.
+#[repr(u16)]
+#[derive(Debug, Clone, Copy, TryFromInt, PartialEq, Eq)]
+#[expect(non_camel_case_types)]
+#[expect(clippy::upper_case_acronyms)]
+pub(super) enum CTaskstatsCmdAttr {
+    /// The TID of the thread as a `u32`
+    PID = 1,
+    /// The PID of the process as a `u32`
+    TGID = 2,
+}
+
+pub(super) const TASKSTATS_CMD_ATTR_MAX: u32 = CTaskstatsCmdAttr::TGID as u32;
+
+/// The attributes of `TASKSTATS_CMD_NEW`.
+#[repr(u16)]
+#[derive(Debug, Clone, Copy, TryFromInt, PartialEq, Eq)]
+#[expect(non_camel_case_types)]
+#[expect(clippy::upper_case_acronyms)]
+pub(super) enum CTaskstatsType {
+    /// The TID of the thread as a `u32`
+    PID = 1,
+    /// The PID of the process as a `u32`
+    TGID = 2,
+    /// The statistics as [`CTaskstats`]
+    STATS = 3,
+    /// The statistics of a thread as a nested array of [`CTaskstatsType::PID`] and
+    /// [`CTaskstatsType::STATS`]
+    AGGR_PID = 4,
+    /// The statistics of a process as a nested array of [`CTaskstatsType::TGID`] and
+    /// [`CTaskstatsType::STATS`]
+    AGGR_TGID = 5,
+}
+
+/// The statistics of a thread or a process (i.e., `struct taskstats` in Linux).
+///
+/// The padding fields are explicit to keep the layout the same as that of Linux, where some
+/// fields are aligned to 8 bytes.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct CTaskstats {
+    pub(super) version: u16,
+    pub(super) _pad0: u16,
+    pub(super) ac_exitcode: u32,
+    pub(super) ac_flag: u8,
+    pub(super) ac_nice: u8,
+    pub(super) _pad1: [u8; 6],
+    // Delay accounting
+    pub(super) cpu_count: u64,
+    pub(super) cpu_delay_total: u64,
+    pub(super) blkio_count: u64,
+    pub(super) blkio_delay_total: u64,
+    pub(super) swapin_count: u64,
+    pub(super) swapin_delay_total: u64,
+    /// The CPU time in nanoseconds
+    pub(super) cpu_run_real_total: u64,
+    pub(super) cpu_run_virtual_total: u64,
+    // Basic accounting
+    pub(super) ac_comm: [u8; TS_COMM_LEN],
+    pub(super) ac_sched: u8,
+    pub(super) _ac_pad: [u8; 3],
+    pub(super) _pad2: u32,
+    pub(super) ac_uid: u32,
+    pub(super) ac_gid: u32,
+    pub(super) ac_pid: u32,
+    pub(super) ac_ppid: u32,
+    /// The start time in seconds since the epoch
+    pub(super) ac_btime: u32,
+    pub(super) _pad3: u32,
+    /// The elapsed time since the start in microseconds
+    pub(super) ac_etime: u64,
+    /// The user CPU time in microseconds
+    pub(super) ac_utime: u64,
+    /// The system CPU time in microseconds
+    pub(super) ac_stime: u64,
+    pub(super) ac_minflt: u64,
+    pub(super) ac_majflt: u64,
+    // Extended accounting
+    pub(super) coremem: u64,
+    pub(super) virtmem: u64,
+    pub(super) hiwater_rss: u64,
+    pub(super) hiwater_vm: u64,
+    pub(super) read_char: u64,
+    pub(super) write_char: u64,
+    pub(super) read_syscalls: u64,
+    pub(super) write_syscalls: u64,
+    pub(super) read_bytes: u64,
+    pub(super) write_bytes: u64,
+    pub(super) cancelled_write_bytes: u64,
+    pub(super) nvcsw: u64,
+    pub(super) nivcsw: u64,
+    pub(super) ac_utimescaled: u64,
+    pub(super) ac_stimescaled: u64,
+    pub(super) cpu_scaled_run_real_total: u64,
+    pub(super) freepages_count: u64,
+    pub(super) freepages_delay_total: u64,
+    pub(super) thrashing_count: u64,
+    pub(super) thrashing_delay_total: u64,
+    /// The start time in seconds since the epoch
+    pub(super) ac_btime64: u64,
+    pub(super) compact_count: u64,
+    pub(super) compact_delay_total: u64,
+    pub(super) ac_tgid: u32,
+    pub(super) _pad4: u32,
+    pub(super) ac_tgetime: u64,
+    pub(super) ac_exe_dev: u64,
+    pub(super) ac_exe_inode: u64,
+    pub(super) wpcopy_count: u64,
+    pub(super) wpcopy_delay_total: u64,
+}
+
+/// The version of [`CTaskstats`].
+pub(super) const TASKSTATS_VERSION: u16 = 13;
+
+/// The length of [`CTaskstats::ac_comm`].
+pub(super) const TS_COMM_LEN: usize = 32;
@@ -0,0 +1,111 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The `NETLINK_GENERIC` protocol.
+//!
+//! The protocol multiplexes families, each of which is identified by a name and a numeric ID.
+//! The IDs are resolved by the controller (see [`ctrl`]), which has a fixed ID. The families
+//! are currently:
+//!  - `nlctrl`, the controller itself;
+//!  - `wireguard`, which configures WireGuard ifaces (see [`wireguard`]);
+//!  - `TASKSTATS`, which reports the statistics of threads and processes (see [`taskstats`]).
+//!
+//! Reference: <https://docs.kernel.org/userspace-api/netlink/intro.html>.
+
+mod ctrl;
+mod message;
+mod taskstats;
+mod wireguard;
+
+use self::message::{
+    CCtrlAttr, CCtrlCmd, CGenlMsgHeader, CTaskstatsCmd, CWgCmd, GENL_CMD_CAP_DO, GENL_CMD_CAP_DUMP,
+    GENL_ID_CTRL, GENL_UNS_ADMIN_PERM, TASKSTATS_CMD_ATTR_MAX, WG_DEVICE_ATTR_MAX,
+};
+use super::{
+    kernel::{KernelProtocol, Request, Response},
+    socket::NetlinkSocket,
+    table::NetlinkSocketTable,
+};
+use crate::prelude::*;
+
+/// A `NETLINK_GENERIC` socket.
+pub type NetlinkGenericSocket = NetlinkSocket<GenericProtocol>;
+
+/// The `NETLINK_GENERIC` protocol.
+pub struct GenericProtocol;
+
+static GENERIC_SOCKET_TABLE: NetlinkSocketTable = NetlinkSocketTable::new();
+
+impl KernelProtocol for GenericProtocol {
+    const IS_NONROOT_RECV_ALLOWED: bool = true;
+    const IS_NONROOT_SEND_ALLOWED: bool = false;
+
+    fn socket_table() -> &'static NetlinkSocketTable {
+        &GENERIC_SOCKET_TABLE
+    }
+
+    fn handle_request(request: &Request) -> Result<Response> {
+        let (header, _) = request.parse_body::<CGenlMsgHeader>()?;
+
+        match request.header.type_ {
+            GENL_ID_CTRL => ctrl::handle_request(request, header.cmd),
+            WIREGUARD_FAMILY_ID => wireguard::handle_request(request, header.cmd),
+            TASKSTATS_FAMILY_ID => taskstats::handle_request(request, header.cmd),
+            _ => return_errno_with_message!(Errno::ENOENT, "the family does not exist"),
+        }
+    }
+}
+
+/// A generic netlink family.
+struct GenericFamily {
+    id: u16,
+    name: &'static str,
+    version: u8,
+    max_attr: u32,
+    /// The commands and their flags (`GENL_*`)
+    ops: &'static [(u8, u32)],
+}
+
+/// The ID of the `wireguard` family.
+///
+/// This is the first ID that Linux allocates dynamically (i.e., `GENL_START_ALLOC`). The user
+/// space should always resolve the ID by the family name.
+const WIREGUARD_FAMILY_ID: u16 = 0x13;
+
+/// The ID of the `TASKSTATS` family.
+const TASKSTATS_FAMILY_ID: u16 = 0x14;
+
+static ALL_FAMILIES: [GenericFamily; 3] = [
+    GenericFamily {
+        id: GENL_ID_CTRL,
+        name: "nlctrl",
+        version: 2,
+        max_attr: CCtrlAttr::OPS as u32,
+        ops: &[(
+            CCtrlCmd::GETFAMILY as u8,
+            GENL_CMD_CAP_DO | GENL_CMD_CAP_DUMP,
+        )],
+    },
+    GenericFamily {
+        id: WIREGUARD_FAMILY_ID,
+        name: "wireguard",
+        version: 1,
+        max_attr: WG_DEVICE_ATTR_MAX,
+        ops: &[
+            (
+                CWgCmd::GET_DEVICE as u8,
+                GENL_UNS_ADMIN_PERM | GENL_CMD_CAP_DUMP,
+            ),
+            (
+                CWgCmd::SET_DEVICE as u8,
+                GENL_UNS_ADMIN_PERM | GENL_CMD_CAP_DO,
+            ),
+        ],
+    },
+    GenericFamily {
+        id: TASKSTATS_FAMILY_ID,
+        name: "TASKSTATS",
+        version: 1,
+        max_attr: TASKSTATS_CMD_ATTR_MAX,
+        ops: &[(CTaskstatsCmd::GET as u8, GENL_CMD_CAP_DO)],
+    },
+];
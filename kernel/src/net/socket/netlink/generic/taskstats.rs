@@ -0,0 +1,151 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The `TASKSTATS` family, which reports the statistics of threads and processes.
+//!
+//! Reference: <https://docs.kernel.org/accounting/taskstats.html>.
+
+use core::{sync::atomic::Ordering, time::Duration};
+
+use super::{
+    message::{
+        CGenlMsgHeader, CTaskstats, CTaskstatsCmd, CTaskstatsCmdAttr, CTaskstatsType,
+        TASKSTATS_VERSION, TS_COMM_LEN,
+    },
+    TASKSTATS_FAMILY_ID,
+};
+use crate::{
+    net::socket::netlink::{
+        kernel::{Request, Response},
+        message::{attr::AttrList, SegmentFlags},
+    },
+    prelude::*,
+    process::{
+        posix_thread::{thread_table, AsPosixThread, PosixThread},
+        process_table, Process,
+    },
+    time::{
+        clocks::{BootTimeClock, ProfClock},
+        SystemTime, START_TIME,
+    },
+};
+
+/// The version of the family.
+const TASKSTATS_GENL_VERSION: u8 = 1;
+
+pub(super) fn handle_request(request: &Request, cmd: u8) -> Result<Response> {
+    match CTaskstatsCmd::try_from(cmd) {
+        Ok(CTaskstatsCmd::GET) if !request.is_dump() => get_stats(request),
+        _ => return_errno_with_message!(Errno::EOPNOTSUPP, "the command is not supported"),
+    }
+}
+
+fn get_stats(request: &Request) -> Result<Response> {
+    let (_, attrs) = request.parse_body::<CGenlMsgHeader>()?;
+    let attrs = AttrList::parse(attrs)?;
+
+    let (aggr_type, id_type, id, stats) =
+        if let Some(tid) = attrs.get_u32(CTaskstatsCmdAttr::PID as u16)? {
+            let thread = thread_table::get_thread(tid)
+                .ok_or_else(|| Error::with_message(Errno::ESRCH, "the thread does not exist"))?;
+            let posix_thread = thread.as_posix_thread().unwrap();
+            let stats = thread_stats(posix_thread, &posix_thread.process());
+            (CTaskstatsType::AGGR_PID, CTaskstatsType::PID, tid, stats)
+        } else if let Some(pid) = attrs.get_u32(CTaskstatsCmdAttr::TGID as u16)? {
+            let process = process_table::get_process(pid)
+                .ok_or_else(|| Error::with_message(Errno::ESRCH, "the process does not exist"))?;
+            let stats = process_stats(&process);
+            (CTaskstatsType::AGGR_TGID, CTaskstatsType::TGID, pid, stats)
+        } else {
+            return_errno_with_message!(Errno::EINVAL, "the thread or process is not specified");
+        };
+
+    let mut writer = request.reply_writer(TASKSTATS_FAMILY_ID, SegmentFlags::empty());
+    writer.write_body(&CGenlMsgHeader {
+        cmd: CTaskstatsCmd::NEW as u8,
+        version: TASKSTATS_GENL_VERSION,
+        _reserved: 0,
+    });
+    writer.write_nested_attr(aggr_type as u16, |writer| {
+        writer.write_attr_u32(id_type as u16, id);
+        writer.write_attr(CTaskstatsType::STATS as u16, stats.as_bytes());
+    });
+
+    Ok(Response::Reply(writer.finish()))
+}
+
+/// Returns the statistics of the thread.
+fn thread_stats(posix_thread: &PosixThread, process: &Process) -> CTaskstats {
+    let mut stats = CTaskstats::new_zeroed();
+    stats.version = TASKSTATS_VERSION;
+    stats.ac_nice = i8::from(process.nice().load(Ordering::Relaxed)) as u8;
+
+    if process.status().is_zombie() {
+        stats.ac_exitcode = process.status().exit_code();
+    }
+
+    let thread_name = posix_thread.thread_name().lock();
+    let comm = match thread_name.as_ref().map(|name| name.name()) {
+        Some(Ok(Some(name))) => name.to_bytes().to_vec(),
+        _ => {
+            let executable_path = process.executable_path();
+            let comm = executable_path.rsplit('/').next().unwrap_or_default();
+            comm.as_bytes().to_vec()
+        }
+    };
+    drop(thread_name);
+    // Like Linux, the command name is truncated and terminated by a null byte.
+    let len = comm.len().min(TS_COMM_LEN - 1);
+    stats.ac_comm[..len].copy_from_slice(&comm[..len]);
+
+    let credentials = posix_thread.credentials();
+    stats.ac_uid = credentials.ruid().into();
+    stats.ac_gid = credentials.rgid().into();
+    stats.ac_pid = posix_thread.tid();
+    stats.ac_tgid = process.pid();
+    stats.ac_ppid = process.parent().pid();
+
+    let boot_time = START_TIME
+        .get()
+        .unwrap()
+        .duration_since(&SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    let start_time = boot_time + process.start_time();
+    stats.ac_btime = start_time.as_secs() as u32;
+    stats.ac_btime64 = start_time.as_secs();
+    stats.ac_tgetime = elapsed_time(process).as_micros() as u64;
+
+    fill_cpu_time(&mut stats, process, posix_thread.prof_clock());
+    stats.ac_utimescaled = stats.ac_utime;
+    stats.ac_stimescaled = stats.ac_stime;
+    stats.cpu_scaled_run_real_total = stats.cpu_run_real_total;
+
+    stats
+}
+
+/// Returns the statistics of all the threads in the process.
+///
+/// Like Linux, only the accumulated fields are reported.
+fn process_stats(process: &Process) -> CTaskstats {
+    let mut stats = CTaskstats::new_zeroed();
+    stats.version = TASKSTATS_VERSION;
+    fill_cpu_time(&mut stats, process, process.prof_clock());
+    stats
+}
+
+fn fill_cpu_time(stats: &mut CTaskstats, process: &Process, prof_clock: &ProfClock) {
+    stats.ac_etime = elapsed_time(process).as_micros() as u64;
+
+    let user_time = prof_clock.user_clock().read_time();
+    let system_time = prof_clock.kernel_clock().read_time();
+    stats.ac_utime = user_time.as_micros() as u64;
+    stats.ac_stime = system_time.as_micros() as u64;
+    stats.cpu_run_real_total = (user_time + system_time).as_nanos() as u64;
+    stats.cpu_run_virtual_total = stats.cpu_run_real_total;
+}
+
+/// Returns the time elapsed since the process was created.
+fn elapsed_time(process: &Process) -> Duration {
+    BootTimeClock::get()
+        .read_time()
+        .saturating_sub(process.start_time())
+}
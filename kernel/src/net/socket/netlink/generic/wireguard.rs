@@ -0,0 +1,299 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The `wireguard` family, which configures WireGuard ifaces.
+//!
+//! Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/drivers/net/wireguard/netlink.c>.
+
+use aster_bigtcp::wire::{IpAddress, IpCidr, IpEndpoint, Ipv4Address, Ipv6Address};
+
+use super::{
+    message::{
+        CGenlMsgHeader, CKernelTimespec, CWgAllowedIpAttr, CWgCmd, CWgDeviceAttr, CWgPeerAttr,
+        WGDEVICE_F_REPLACE_PEERS, WGPEER_F_REMOVE_ME, WGPEER_F_REPLACE_ALLOWEDIPS,
+        WGPEER_F_UPDATE_ONLY,
+    },
+    WIREGUARD_FAMILY_ID,
+};
+use crate::{
+    net::{
+        iface::{
+            find_iface_by_index, find_iface_by_name, WireGuard, WireGuardKey, WireGuardPeerConfig,
+            WireGuardPeerUpdate, WireGuardUpdate,
+        },
+        socket::netlink::{
+            kernel::{check_net_admin, Request, Response},
+            message::{attr::AttrList, SegmentFlags, SegmentWriter},
+        },
+    },
+    prelude::*,
+    util::net::{CSocketAddrFamily, CSocketAddrInet, CSocketAddrInet6},
+};
+
+/// The version of the WireGuard protocol.
+const WG_PROTOCOL_VERSION: u32 = 1;
+
+/// The version of the family.
+const WG_GENL_VERSION: u8 = 1;
+
+pub(super) fn handle_request(request: &Request, cmd: u8) -> Result<Response> {
+    // Like Linux, all commands require `CAP_NET_ADMIN`, including `WG_CMD_GET_DEVICE`.
+    check_net_admin()?;
+
+    match CWgCmd::try_from(cmd) {
+        Ok(CWgCmd::GET_DEVICE) if request.is_dump() => get_device(request),
+        Ok(CWgCmd::SET_DEVICE) if !request.is_dump() => set_device(request),
+        _ => return_errno_with_message!(Errno::EOPNOTSUPP, "the command is not supported"),
+    }
+}
+
+fn get_device(request: &Request) -> Result<Response> {
+    let (_, attrs) = request.parse_body::<CGenlMsgHeader>()?;
+    let attrs = AttrList::parse(attrs)?;
+    let wireguard = lookup_device(&attrs)?;
+
+    let config = wireguard.config();
+    let iface = wireguard.iface();
+
+    let mut writer = request.reply_writer(WIREGUARD_FAMILY_ID, SegmentFlags::MULTI);
+    writer.write_body(&CGenlMsgHeader {
+        cmd: CWgCmd::GET_DEVICE as u8,
+        version: WG_GENL_VERSION,
+        _reserved: 0,
+    });
+
+    writer.write_attr_u16(CWgDeviceAttr::LISTEN_PORT as u16, config.listen_port);
+    writer.write_attr_u32(CWgDeviceAttr::FWMARK as u16, config.fwmark);
+    writer.write_attr_u32(CWgDeviceAttr::IFINDEX as u16, iface.index());
+    writer.write_attr_str(CWgDeviceAttr::IFNAME as u16, iface.name());
+    if let Some(private_key) = config.private_key.as_ref() {
+        writer.write_attr(CWgDeviceAttr::PRIVATE_KEY as u16, private_key);
+    }
+    if let Some(public_key) = config.public_key.as_ref() {
+        writer.write_attr(CWgDeviceAttr::PUBLIC_KEY as u16, public_key);
+    }
+    writer.write_nested_attr(CWgDeviceAttr::PEERS as u16, |writer| {
+        for peer in config.peers.iter() {
+            write_peer(writer, peer);
+        }
+    });
+
+    // Unlike Linux, all the peers are put in one segment.
+    Ok(Response::Dump(vec![writer.finish()]))
+}
+
+fn write_peer(writer: &mut SegmentWriter, peer: &WireGuardPeerConfig) {
+    // The entries of nested arrays have no types.
+    writer.write_nested_attr(0, |writer| {
+        writer.write_attr(CWgPeerAttr::PUBLIC_KEY as u16, &peer.public_key);
+        writer.write_attr(CWgPeerAttr::PRESHARED_KEY as u16, &peer.preshared_key);
+
+        let last_handshake_time = peer.last_handshake_time.unwrap_or_default();
+        let timespec = CKernelTimespec {
+            sec: last_handshake_time.as_secs() as i64,
+            nsec: last_handshake_time.subsec_nanos() as i64,
+        };
+        writer.write_attr(CWgPeerAttr::LAST_HANDSHAKE_TIME as u16, timespec.as_bytes());
+
+        writer.write_attr_u16(
+            CWgPeerAttr::PERSISTENT_KEEPALIVE_INTERVAL as u16,
+            peer.persistent_keepalive_interval,
+        );
+        writer.write_attr_u64(CWgPeerAttr::TX_BYTES as u16, peer.tx_bytes);
+        writer.write_attr_u64(CWgPeerAttr::RX_BYTES as u16, peer.rx_bytes);
+        writer.write_attr_u32(CWgPeerAttr::PROTOCOL_VERSION as u16, WG_PROTOCOL_VERSION);
+
+        match peer.endpoint {
+            Some(IpEndpoint {
+                addr: IpAddress::Ipv4(addr),
+                port,
+            }) => {
+                let sockaddr = CSocketAddrInet::from((addr, port));
+                writer.write_attr(CWgPeerAttr::ENDPOINT as u16, sockaddr.as_bytes());
+            }
+            Some(IpEndpoint {
+                addr: IpAddress::Ipv6(addr),
+                port,
+            }) => {
+                let sockaddr = CSocketAddrInet6::from((addr, port));
+                writer.write_attr(CWgPeerAttr::ENDPOINT as u16, sockaddr.as_bytes());
+            }
+            None => (),
+        }
+
+        writer.write_nested_attr(CWgPeerAttr::ALLOWEDIPS as u16, |writer| {
+            for cidr in peer.allowed_ips.iter() {
+                write_allowed_ip(writer, cidr);
+            }
+        });
+    });
+}
+
+fn write_allowed_ip(writer: &mut SegmentWriter, cidr: &IpCidr) {
+    writer.write_nested_attr(0, |writer| {
+        writer.write_attr_u8(CWgAllowedIpAttr::CIDR_MASK as u16, cidr.prefix_len());
+        match cidr.address() {
+            IpAddress::Ipv4(addr) => {
+                writer.write_attr_u16(
+                    CWgAllowedIpAttr::FAMILY as u16,
+                    CSocketAddrFamily::AF_INET as u16,
+                );
+                writer.write_attr_ipv4_addr(CWgAllowedIpAttr::IPADDR as u16, addr);
+            }
+            IpAddress::Ipv6(addr) => {
+                writer.write_attr_u16(
+                    CWgAllowedIpAttr::FAMILY as u16,
+                    CSocketAddrFamily::AF_INET6 as u16,
+                );
+                writer.write_attr(CWgAllowedIpAttr::IPADDR as u16, &addr.octets());
+            }
+        }
+    });
+}
+
+fn set_device(request: &Request) -> Result<Response> {
+    let (_, attrs) = request.parse_body::<CGenlMsgHeader>()?;
+    let attrs = AttrList::parse(attrs)?;
+    let wireguard = lookup_device(&attrs)?;
+
+    // The whole request is parsed before the iface is updated, so an invalid request changes
+    // nothing.
+    let flags = attrs.get_u32(CWgDeviceAttr::FLAGS as u16)?.unwrap_or(0);
+    if flags & !WGDEVICE_F_REPLACE_PEERS != 0 {
+        return_errno_with_message!(Errno::EOPNOTSUPP, "the device flags are not supported");
+    }
+
+    let mut update = WireGuardUpdate {
+        private_key: parse_key(&attrs, CWgDeviceAttr::PRIVATE_KEY as u16)?,
+        listen_port: attrs.get_u16(CWgDeviceAttr::LISTEN_PORT as u16)?,
+        fwmark: attrs.get_u32(CWgDeviceAttr::FWMARK as u16)?,
+        replace_peers: flags & WGDEVICE_F_REPLACE_PEERS != 0,
+        peers: Vec::new(),
+    };
+
+    if let Some(peers) = attrs.get(CWgDeviceAttr::PEERS as u16) {
+        for peer in AttrList::parse(peers)?.payloads() {
+            update.peers.push(parse_peer(peer)?);
+        }
+    }
+
+    wireguard.update(update)?;
+
+    Ok(Response::Done)
+}
+
+fn parse_peer(bytes: &[u8]) -> Result<WireGuardPeerUpdate> {
+    let attrs = AttrList::parse(bytes)?;
+
+    let Some(public_key) = parse_key(&attrs, CWgPeerAttr::PUBLIC_KEY as u16)? else {
+        return_errno_with_message!(Errno::EINVAL, "the public key of the peer is missing");
+    };
+    let mut update = WireGuardPeerUpdate::new(public_key);
+
+    let flags = attrs.get_u32(CWgPeerAttr::FLAGS as u16)?.unwrap_or(0);
+    if flags & !(WGPEER_F_REMOVE_ME | WGPEER_F_REPLACE_ALLOWEDIPS | WGPEER_F_UPDATE_ONLY) != 0 {
+        return_errno_with_message!(Errno::EOPNOTSUPP, "the peer flags are not supported");
+    }
+    update.remove = flags & WGPEER_F_REMOVE_ME != 0;
+    update.update_only = flags & WGPEER_F_UPDATE_ONLY != 0;
+    update.replace_allowed_ips = flags & WGPEER_F_REPLACE_ALLOWEDIPS != 0;
+
+    if let Some(version) = attrs.get_u32(CWgPeerAttr::PROTOCOL_VERSION as u16)? {
+        if version != WG_PROTOCOL_VERSION {
+            return_errno_with_message!(
+                Errno::EPROTONOSUPPORT,
+                "the protocol version is not supported"
+            );
+        }
+    }
+
+    update.preshared_key = parse_key(&attrs, CWgPeerAttr::PRESHARED_KEY as u16)?;
+    update.persistent_keepalive_interval =
+        attrs.get_u16(CWgPeerAttr::PERSISTENT_KEEPALIVE_INTERVAL as u16)?;
+    if let Some(endpoint) = attrs.get(CWgPeerAttr::ENDPOINT as u16) {
+        update.endpoint = parse_endpoint(endpoint);
+    }
+
+    if let Some(allowed_ips) = attrs.get(CWgPeerAttr::ALLOWEDIPS as u16) {
+        for allowed_ip in AttrList::parse(allowed_ips)?.payloads() {
+            update.allowed_ips.push(parse_allowed_ip(allowed_ip)?);
+        }
+    }
+
+    Ok(update)
+}
+
+/// Parses the endpoint of a peer.
+///
+/// Like Linux, an endpoint that is neither `struct sockaddr_in` nor `struct sockaddr_in6` is
+/// ignored.
+fn parse_endpoint(bytes: &[u8]) -> Option<IpEndpoint> {
+    let family = u16::from_ne_bytes(bytes.get(..size_of::<u16>())?.try_into().unwrap());
+
+    if bytes.len() == size_of::<CSocketAddrInet>() && family == CSocketAddrFamily::AF_INET as u16 {
+        let (addr, port) = CSocketAddrInet::from_bytes(bytes).into();
+        Some(IpEndpoint::new(IpAddress::Ipv4(addr), port))
+    } else if bytes.len() == size_of::<CSocketAddrInet6>()
+        && family == CSocketAddrFamily::AF_INET6 as u16
+    {
+        let (addr, port) = CSocketAddrInet6::from_bytes(bytes).into();
+        Some(IpEndpoint::new(IpAddress::Ipv6(addr), port))
+    } else {
+        None
+    }
+}
+
+fn parse_allowed_ip(bytes: &[u8]) -> Result<IpCidr> {
+    let attrs = AttrList::parse(bytes)?;
+
+    let family = attrs.get_u16(CWgAllowedIpAttr::FAMILY as u16)?;
+    let addr = attrs.get(CWgAllowedIpAttr::IPADDR as u16);
+    let prefix_len = attrs.get_u8(CWgAllowedIpAttr::CIDR_MASK as u16)?;
+    let (Some(family), Some(addr), Some(prefix_len)) = (family, addr, prefix_len) else {
+        return_errno_with_message!(Errno::EINVAL, "the allowed IP is incomplete");
+    };
+
+    let addr = if family == CSocketAddrFamily::AF_INET as u16 && prefix_len <= 32 {
+        let octets: [u8; 4] = addr.try_into().map_err(|_| {
+            Error::with_message(Errno::EINVAL, "the IPv4 address length is invalid")
+        })?;
+        IpAddress::Ipv4(Ipv4Address::from(octets))
+    } else if family == CSocketAddrFamily::AF_INET6 as u16 && prefix_len <= 128 {
+        let octets: [u8; 16] = addr.try_into().map_err(|_| {
+            Error::with_message(Errno::EINVAL, "the IPv6 address length is invalid")
+        })?;
+        IpAddress::Ipv6(Ipv6Address::from(octets))
+    } else {
+        return_errno_with_message!(Errno::EINVAL, "the allowed IP is invalid");
+    };
+
+    Ok(IpCidr::new(addr, prefix_len))
+}
+
+fn parse_key(attrs: &AttrList, type_: u16) -> Result<Option<WireGuardKey>> {
+    attrs
+        .get(type_)
+        .map(|payload| {
+            payload
+                .try_into()
+                .map_err(|_| Error::with_message(Errno::EINVAL, "the key length is invalid"))
+        })
+        .transpose()
+}
+
+/// Finds the WireGuard iface specified by `WGDEVICE_A_IFINDEX` or `WGDEVICE_A_IFNAME`.
+fn lookup_device(attrs: &AttrList) -> Result<Arc<WireGuard>> {
+    let iface = if let Some(index) = attrs.get_u32(CWgDeviceAttr::IFINDEX as u16)? {
+        find_iface_by_index(index)
+    } else if let Some(name) = attrs.get_str(CWgDeviceAttr::IFNAME as u16)? {
+        find_iface_by_name(name)
+    } else {
+        return_errno_with_message!(Errno::EBADR, "the device is not specified");
+    };
+    let Some(iface) = iface else {
+        return_errno_with_message!(Errno::ENODEV, "the device does not exist");
+    };
+
+    WireGuard::find(iface.index()).ok_or_else(|| {
+        Error::with_message(Errno::EOPNOTSUPP, "the device is not a WireGuard iface")
+    })
+}
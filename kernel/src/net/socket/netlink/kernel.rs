@@ -0,0 +1,185 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The kernel side of netlink protocols.
+//!
+//! When a netlink socket sends a message to the kernel, the message is split into segments and
+//! each request segment is handed over to the protocol (see [`KernelProtocol`]). This module
+//! takes care of the protocol-independent parts, such as reporting errors, sending
+//! acknowledgements, and terminating dumps.
+//!
+//! Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/net/netlink/af_netlink.c#L2476>.
+
+use super::{
+    message::{
+        align_segment_len, split_segments, CSegmentHeader, SegmentFlags, SegmentWriter,
+        MIN_PROTOCOL_SEGMENT_TYPE,
+    },
+    table::NetlinkSocketTable,
+};
+use crate::{
+    prelude::*,
+    process::{credentials::capabilities::CapSet, posix_thread::AsPosixThread},
+};
+
+/// A netlink protocol that is implemented by the kernel.
+pub trait KernelProtocol: Send + Sync + 'static {
+    /// Whether sockets without `CAP_NET_ADMIN` can join multicast groups.
+    const IS_NONROOT_RECV_ALLOWED: bool;
+    /// Whether sockets without `CAP_NET_ADMIN` can send messages to other sockets or to
+    /// multicast groups.
+    const IS_NONROOT_SEND_ALLOWED: bool;
+
+    /// Returns the table of the sockets of the protocol.
+    fn socket_table() -> &'static NetlinkSocketTable;
+
+    /// Handles a request segment.
+    fn handle_request(request: &Request) -> Result<Response>;
+}
+
+/// A request segment sent to the kernel.
+pub struct Request<'a> {
+    /// The header of the segment
+    pub(super) header: CSegmentHeader,
+    /// The payload of the segment
+    pub(super) payload: &'a [u8],
+    /// The port number of the sending socket
+    pub(super) port: u32,
+}
+
+impl<'a> Request<'a> {
+    pub(super) fn flags(&self) -> SegmentFlags {
+        self.header.flags()
+    }
+
+    /// Returns whether the request asks for a dump.
+    pub(super) fn is_dump(&self) -> bool {
+        self.flags().contains(SegmentFlags::DUMP)
+    }
+
+    /// Parses the protocol-specific body of the request.
+    ///
+    /// This method returns the body and the remaining bytes (i.e., the attributes). Like Linux, a
+    /// dump request can have a shorter body (e.g., `struct rtgenmsg` used by legacy programs),
+    /// in which case the missing bytes are treated as zeros.
+    pub(super) fn parse_body<T: Pod>(&self) -> Result<(T, &'a [u8])> {
+        let body_len = size_of::<T>();
+
+        if self.payload.len() >= body_len {
+            let body = T::from_bytes(&self.payload[..body_len]);
+            let attrs = &self.payload[align_segment_len(body_len).min(self.payload.len())..];
+            return Ok((body, attrs));
+        }
+
+        if !self.is_dump() {
+            return_errno_with_message!(Errno::EINVAL, "the request body is too short");
+        }
+
+        let mut body = T::new_zeroed();
+        body.as_bytes_mut()[..self.payload.len()].copy_from_slice(self.payload);
+        Ok((body, &[]))
+    }
+
+    /// Starts a reply segment with the given type and flags.
+    pub(super) fn reply_writer(&self, type_: u16, flags: SegmentFlags) -> SegmentWriter {
+        SegmentWriter::new_reply(type_, flags, &self.header, self.port)
+    }
+
+    /// Starts a segment that notifies the multicast groups of the changes made by the request.
+    pub(super) fn notification_writer(&self, type_: u16) -> SegmentWriter {
+        SegmentWriter::new(type_, SegmentFlags::empty(), self.header.seq, self.port)
+    }
+}
+
+/// The response to a request segment.
+pub enum Response {
+    /// No reply except for the acknowledgement (if requested).
+    Done,
+    /// A single reply segment, which will be followed by the acknowledgement (if requested).
+    Reply(Vec<u8>),
+    /// A dump that consists of multiple segments, which will be terminated by `NLMSG_DONE`.
+    Dump(Vec<Vec<u8>>),
+}
+
+/// Processes a message sent to the kernel by the socket bound to the port `port`.
+///
+/// This method returns the messages that should be sent back to the socket.
+pub(super) fn process_message<P: KernelProtocol>(bytes: &[u8], port: u32) -> Vec<Vec<u8>> {
+    let mut replies = Vec::new();
+
+    for (header, payload) in split_segments(bytes) {
+        let flags = header.flags();
+
+        // Like Linux, segments that are not requests and control segments are acknowledged
+        // without being processed.
+        let result =
+            if !flags.contains(SegmentFlags::REQUEST) || header.type_ < MIN_PROTOCOL_SEGMENT_TYPE {
+                Ok(Response::Done)
+            } else {
+                let request = Request {
+                    header,
+                    payload,
+                    port,
+                };
+                P::handle_request(&request)
+            };
+
+        match result {
+            Ok(Response::Done) => (),
+            Ok(Response::Reply(segment)) => replies.push(segment),
+            Ok(Response::Dump(segments)) => {
+                pack_dump(segments, &header, port, &mut replies);
+                continue;
+            }
+            Err(err) => {
+                replies.push(SegmentWriter::new_error(&header, port, Some(err.error())));
+                continue;
+            }
+        }
+
+        if flags.contains(SegmentFlags::ACK) {
+            replies.push(SegmentWriter::new_error(&header, port, None));
+        }
+    }
+
+    replies
+}
+
+/// Packs the segments of a dump into messages.
+///
+/// Like Linux, a message contains as many segments as possible, as long as its size does not
+/// exceed [`MAX_DUMP_MESSAGE_LEN`]. The last message is terminated by `NLMSG_DONE`.
+fn pack_dump(
+    segments: Vec<Vec<u8>>,
+    request_header: &CSegmentHeader,
+    port: u32,
+    messages: &mut Vec<Vec<u8>>,
+) {
+    let mut message = Vec::new();
+
+    let done = SegmentWriter::new_done(request_header, port);
+    for segment in segments.into_iter().chain(core::iter::once(done)) {
+        if !message.is_empty() && message.len() + segment.len() > MAX_DUMP_MESSAGE_LEN {
+            messages.push(core::mem::take(&mut message));
+        }
+        message.extend_from_slice(&segment);
+    }
+
+    messages.push(message);
+}
+
+/// The maximum size of a message in a dump.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/linux/netlink.h#L264>.
+const MAX_DUMP_MESSAGE_LEN: usize = 8192;
+
+/// Checks whether the current thread has `CAP_NET_ADMIN`.
+pub(super) fn check_net_admin() -> Result<()> {
+    let current = current_thread!();
+    let credentials = current.as_posix_thread().unwrap().credentials();
+
+    if !credentials.effective_capset().contains(CapSet::NET_ADMIN) {
+        return_errno_with_message!(Errno::EPERM, "`CAP_NET_ADMIN` is required");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,209 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Netlink attributes.
+//!
+//! An attribute (i.e., `struct nlattr` in Linux) is a type-length-value (TLV) entry that starts
+//! with a header, followed by the payload. Attributes are aligned in the same way as segments.
+//!
+//! Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/netlink.h#L205>.
+
+use aster_bigtcp::wire::Ipv4Address;
+
+use super::{align_segment_len, SegmentWriter};
+use crate::prelude::*;
+
+/// The header of a netlink attribute (i.e., `struct nlattr` in Linux).
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct CAttrHeader {
+    /// The length of the attribute, including the header
+    len: u16,
+    /// The type of the attribute, along with the flags
+    type_: u16,
+}
+
+const ATTR_HEADER_LEN: usize = size_of::<CAttrHeader>();
+
+/// The flag that indicates that the attribute contains nested attributes.
+const NLA_F_NESTED: u16 = 1 << 15;
+/// The flag that indicates that the payload is stored in the network byte order.
+const NLA_F_NET_BYTEORDER: u16 = 1 << 14;
+const NLA_TYPE_MASK: u16 = !(NLA_F_NESTED | NLA_F_NET_BYTEORDER);
+
+/// A list of parsed netlink attributes.
+pub(in crate::net::socket::netlink) struct AttrList<'a> {
+    attrs: Vec<(u16, &'a [u8])>,
+}
+
+impl<'a> AttrList<'a> {
+    /// Parses the attributes in `bytes`.
+    ///
+    /// Like Linux, trailing bytes that are too short to form an attribute header are ignored.
+    pub(in crate::net::socket::netlink) fn parse(mut bytes: &'a [u8]) -> Result<Self> {
+        let mut attrs = Vec::new();
+
+        while bytes.len() >= ATTR_HEADER_LEN {
+            let header = CAttrHeader::from_bytes(&bytes[..ATTR_HEADER_LEN]);
+            let len = header.len as usize;
+            if len < ATTR_HEADER_LEN || len > bytes.len() {
+                return_errno_with_message!(Errno::EINVAL, "the attribute length is invalid");
+            }
+
+            attrs.push((header.type_ & NLA_TYPE_MASK, &bytes[ATTR_HEADER_LEN..len]));
+            bytes = &bytes[align_segment_len(len).min(bytes.len())..];
+        }
+
+        Ok(Self { attrs })
+    }
+
+    /// Returns the types of all the attributes.
+    pub(in crate::net::socket::netlink) fn types(&self) -> impl Iterator<Item = u16> + '_ {
+        self.attrs.iter().map(|(type_, _)| *type_)
+    }
+
+    /// Returns the payloads of all the attributes.
+    ///
+    /// This is useful for nested arrays, where the types of the attributes are ignored.
+    pub(in crate::net::socket::netlink) fn payloads(&self) -> impl Iterator<Item = &'a [u8]> + '_ {
+        self.attrs.iter().map(|(_, payload)| *payload)
+    }
+
+    /// Returns the payload of the attribute of the given type.
+    ///
+    /// If there are multiple attributes of the same type, the last one takes effect.
+    pub(in crate::net::socket::netlink) fn get(&self, type_: u16) -> Option<&'a [u8]> {
+        self.attrs
+            .iter()
+            .rev()
+            .find(|(attr_type, _)| *attr_type == type_)
+            .map(|(_, payload)| *payload)
+    }
+
+    pub(in crate::net::socket::netlink) fn get_u8(&self, type_: u16) -> Result<Option<u8>> {
+        self.get(type_)
+            .map(|payload| {
+                payload
+                    .first()
+                    .copied()
+                    .ok_or_else(|| Error::with_message(Errno::EINVAL, "the attribute is too short"))
+            })
+            .transpose()
+    }
+
+    pub(in crate::net::socket::netlink) fn get_u16(&self, type_: u16) -> Result<Option<u16>> {
+        self.get(type_)
+            .map(|payload| {
+                let bytes = payload.get(..size_of::<u16>()).ok_or_else(|| {
+                    Error::with_message(Errno::EINVAL, "the attribute is too short")
+                })?;
+                Ok(u16::from_ne_bytes(bytes.try_into().unwrap()))
+            })
+            .transpose()
+    }
+
+    pub(in crate::net::socket::netlink) fn get_u32(&self, type_: u16) -> Result<Option<u32>> {
+        self.get(type_)
+            .map(|payload| {
+                let bytes = payload.get(..size_of::<u32>()).ok_or_else(|| {
+                    Error::with_message(Errno::EINVAL, "the attribute is too short")
+                })?;
+                Ok(u32::from_ne_bytes(bytes.try_into().unwrap()))
+            })
+            .transpose()
+    }
+
+    pub(in crate::net::socket::netlink) fn get_ipv4_addr(
+        &self,
+        type_: u16,
+    ) -> Result<Option<Ipv4Address>> {
+        self.get(type_)
+            .map(|payload| {
+                let octets: [u8; 4] = payload.try_into().map_err(|_| {
+                    Error::with_message(Errno::EINVAL, "the IPv4 address length is invalid")
+                })?;
+                Ok(Ipv4Address::from(octets))
+            })
+            .transpose()
+    }
+
+    /// Returns the string of the attribute of the given type.
+    ///
+    /// The string may or may not be terminated by a null byte.
+    pub(in crate::net::socket::netlink) fn get_str(&self, type_: u16) -> Result<Option<&'a str>> {
+        self.get(type_)
+            .map(|payload| {
+                let bytes = match payload.iter().position(|byte| *byte == 0) {
+                    Some(nul_pos) => &payload[..nul_pos],
+                    None => payload,
+                };
+                core::str::from_utf8(bytes).map_err(|_| {
+                    Error::with_message(Errno::EINVAL, "the string is not valid UTF-8")
+                })
+            })
+            .transpose()
+    }
+}
+
+impl SegmentWriter {
+    /// Writes an attribute with the given type and payload.
+    pub(in crate::net::socket::netlink) fn write_attr(&mut self, type_: u16, payload: &[u8]) {
+        let header = CAttrHeader {
+            len: (ATTR_HEADER_LEN + payload.len()) as u16,
+            type_,
+        };
+
+        self.write_aligned(header.as_bytes());
+        self.write_aligned(payload);
+    }
+
+    pub(in crate::net::socket::netlink) fn write_attr_u8(&mut self, type_: u16, value: u8) {
+        self.write_attr(type_, &[value]);
+    }
+
+    pub(in crate::net::socket::netlink) fn write_attr_u16(&mut self, type_: u16, value: u16) {
+        self.write_attr(type_, &value.to_ne_bytes());
+    }
+
+    pub(in crate::net::socket::netlink) fn write_attr_u32(&mut self, type_: u16, value: u32) {
+        self.write_attr(type_, &value.to_ne_bytes());
+    }
+
+    pub(in crate::net::socket::netlink) fn write_attr_u64(&mut self, type_: u16, value: u64) {
+        self.write_attr(type_, &value.to_ne_bytes());
+    }
+
+    pub(in crate::net::socket::netlink) fn write_attr_ipv4_addr(
+        &mut self,
+        type_: u16,
+        addr: Ipv4Address,
+    ) {
+        self.write_attr(type_, &addr.octets());
+    }
+
+    /// Writes an attribute that contains a null-terminated string.
+    pub(in crate::net::socket::netlink) fn write_attr_str(&mut self, type_: u16, value: &str) {
+        let mut payload = Vec::with_capacity(value.len() + 1);
+        payload.extend_from_slice(value.as_bytes());
+        payload.push(0);
+
+        self.write_attr(type_, &payload);
+    }
+
+    /// Writes an attribute whose payload contains the nested attributes written by `f`.
+    pub(in crate::net::socket::netlink) fn write_nested_attr<F>(&mut self, type_: u16, f: F)
+    where
+        F: FnOnce(&mut Self),
+    {
+        let start = self.buffer.len();
+        let header = CAttrHeader {
+            len: 0,
+            type_: type_ | NLA_F_NESTED,
+        };
+        self.write_aligned(header.as_bytes());
+
+        f(self);
+
+        let len = (self.buffer.len() - start) as u16;
+        self.buffer[start..start + size_of::<u16>()].copy_from_slice(&len.to_ne_bytes());
+    }
+}
@@ -0,0 +1,240 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The netlink message format.
+//!
+//! A netlink message consists of one or more segments. Each segment starts with a header (i.e.,
+//! `struct nlmsghdr` in Linux), followed by the payload. The payload of a request or response
+//! usually consists of a protocol-specific body (e.g., `struct ifinfomsg` for `NETLINK_ROUTE`),
+//! followed by a list of attributes (see [`attr`]).
+//!
+//! Reference: <https://docs.kernel.org/userspace-api/netlink/intro.html>.
+
+pub(super) mod attr;
+
+use crate::prelude::*;
+
+/// The header of a netlink message segment (i.e., `struct nlmsghdr` in Linux).
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/netlink.h#L52>.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct CSegmentHeader {
+    /// The length of the segment, including the header
+    pub(super) len: u32,
+    /// The type of the segment content
+    pub(super) type_: u16,
+    /// The additional flags
+    pub(super) flags: u16,
+    /// The sequence number
+    pub(super) seq: u32,
+    /// The port ID of the sending socket
+    pub(super) pid: u32,
+}
+
+pub(super) const SEGMENT_HEADER_LEN: usize = size_of::<CSegmentHeader>();
+
+impl CSegmentHeader {
+    pub(super) fn flags(&self) -> SegmentFlags {
+        SegmentFlags::from_bits_truncate(self.flags)
+    }
+}
+
+bitflags! {
+    /// The flags of a netlink message segment.
+    ///
+    /// Note that the meaning of the flags greater than or equal to 0x100 depends on the type of
+    /// the request. See [`SegmentFlags::DUMP`] and [`SegmentFlags::REPLACE`] for details.
+    ///
+    /// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/netlink.h#L54>.
+    pub(super) struct SegmentFlags: u16 {
+        /// It is a request.
+        const REQUEST = 0x01;
+        /// It is a part of a multi-segment message, which is terminated by `NLMSG_DONE`.
+        const MULTI = 0x02;
+        /// A reply with an error code (zero for success) is requested.
+        const ACK = 0x04;
+        /// The request is echoed.
+        const ECHO = 0x08;
+        /// The dump was inconsistent due to changes during the dump.
+        const DUMP_INTR = 0x10;
+        /// The dump was filtered as requested.
+        const DUMP_FILTERED = 0x20;
+
+        // Modifiers of GET requests.
+
+        /// Returns the complete table instead of a single entry.
+        const ROOT = 0x100;
+        /// Returns all the entries that match the criteria.
+        const MATCH = 0x200;
+        /// Returns an atomic snapshot of the table.
+        const ATOMIC = 0x400;
+        /// Dumps the table (i.e., `ROOT | MATCH`).
+        const DUMP = 0x300;
+
+        // Modifiers of NEW requests.
+
+        /// Replaces the existing entry.
+        const REPLACE = 0x100;
+        /// Does not touch the entry if it already exists.
+        const EXCL = 0x200;
+        /// Creates the entry if it does not exist.
+        const CREATE = 0x400;
+        /// Adds the entry to the end of the list.
+        const APPEND = 0x800;
+    }
+}
+
+/// The types of netlink message segments that are common to all protocols.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/netlink.h#L110>.
+#[repr(u16)]
+#[derive(Debug, Clone, Copy, TryFromInt, PartialEq, Eq)]
+#[expect(clippy::upper_case_acronyms)]
+pub(super) enum CSegmentType {
+    /// Nothing (the segment should be ignored)
+    NOOP = 1,
+    /// An error or an acknowledgement
+    ERROR = 2,
+    /// The end of a multi-segment message
+    DONE = 3,
+    /// Data lost
+    OVERRUN = 4,
+}
+
+/// The minimum segment type that can be used by protocols.
+///
+/// The types below are reserved for control segments (see [`CSegmentType`]).
+pub(super) const MIN_PROTOCOL_SEGMENT_TYPE: u16 = 0x10;
+
+/// The payload of an `NLMSG_ERROR` segment (i.e., `struct nlmsgerr` in Linux).
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/netlink.h#L118>.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct CErrorPayload {
+    /// The negative error number, or zero for an acknowledgement
+    error: i32,
+    /// The header of the segment that causes the error
+    request_header: CSegmentHeader,
+}
+
+/// Aligns the length of a segment (i.e., `NLMSG_ALIGN` in Linux).
+pub(super) const fn align_segment_len(len: usize) -> usize {
+    len.next_multiple_of(SEGMENT_ALIGN)
+}
+
+const SEGMENT_ALIGN: usize = 4;
+
+/// Splits a netlink message into segments.
+///
+/// Each segment is returned as its header and its payload. Like Linux, the remaining bytes are
+/// silently ignored once a malformed segment is found.
+pub(super) fn split_segments(mut bytes: &[u8]) -> impl Iterator<Item = (CSegmentHeader, &[u8])> {
+    core::iter::from_fn(move || {
+        if bytes.len() < SEGMENT_HEADER_LEN {
+            return None;
+        }
+
+        let header = CSegmentHeader::from_bytes(&bytes[..SEGMENT_HEADER_LEN]);
+        let len = header.len as usize;
+        if len < SEGMENT_HEADER_LEN || len > bytes.len() {
+            return None;
+        }
+
+        let payload = &bytes[SEGMENT_HEADER_LEN..len];
+        bytes = &bytes[align_segment_len(len).min(bytes.len())..];
+
+        Some((header, payload))
+    })
+}
+
+/// A writer that builds a netlink message segment.
+pub(super) struct SegmentWriter {
+    buffer: Vec<u8>,
+}
+
+impl SegmentWriter {
+    /// Starts a segment with the header built from the given fields.
+    pub(super) fn new(type_: u16, flags: SegmentFlags, seq: u32, pid: u32) -> Self {
+        let header = CSegmentHeader {
+            len: 0,
+            type_,
+            flags: flags.bits(),
+            seq,
+            pid,
+        };
+
+        Self {
+            buffer: header.as_bytes().to_vec(),
+        }
+    }
+
+    /// Starts a segment that replies to the request whose header is `request_header`.
+    ///
+    /// The reply is sent to the socket whose port number is `port`.
+    pub(super) fn new_reply(
+        type_: u16,
+        flags: SegmentFlags,
+        request_header: &CSegmentHeader,
+        port: u32,
+    ) -> Self {
+        Self::new(type_, flags, request_header.seq, port)
+    }
+
+    /// Builds an `NLMSG_ERROR` segment that reports `error` (or an acknowledgement if `error`
+    /// is `None`) for the request whose header is `request_header`.
+    ///
+    /// Unlike Linux, the payload of the request is not included, which is the same as the
+    /// behavior when `NETLINK_CAP_ACK` is set.
+    pub(super) fn new_error(
+        request_header: &CSegmentHeader,
+        port: u32,
+        error: Option<Errno>,
+    ) -> Vec<u8> {
+        let payload = CErrorPayload {
+            error: error.map_or(0, |errno| -(errno as i32)),
+            request_header: *request_header,
+        };
+
+        let mut writer = Self::new_reply(
+            CSegmentType::ERROR as u16,
+            SegmentFlags::empty(),
+            request_header,
+            port,
+        );
+        writer.write_body(&payload);
+        writer.finish()
+    }
+
+    /// Builds an `NLMSG_DONE` segment that terminates the multi-segment reply to the request
+    /// whose header is `request_header`.
+    pub(super) fn new_done(request_header: &CSegmentHeader, port: u32) -> Vec<u8> {
+        let mut writer = Self::new_reply(
+            CSegmentType::DONE as u16,
+            SegmentFlags::MULTI,
+            request_header,
+            port,
+        );
+        writer.write_body(&0i32);
+        writer.finish()
+    }
+
+    /// Writes the protocol-specific body.
+    pub(super) fn write_body<T: Pod>(&mut self, body: &T) {
+        self.write_aligned(body.as_bytes());
+    }
+
+    /// Writes bytes and pads them to the alignment.
+    pub(super) fn write_aligned(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+        let padded_len = align_segment_len(self.buffer.len());
+        self.buffer.resize(padded_len, 0);
+    }
+
+    /// Finishes the segment and returns its bytes.
+    pub(super) fn finish(mut self) -> Vec<u8> {
+        let len = self.buffer.len() as u32;
+        self.buffer[..size_of::<u32>()].copy_from_slice(&len.to_ne_bytes());
+        self.buffer
+    }
+}
@@ -0,0 +1,80 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Netlink sockets.
+//!
+//! Netlink sockets are datagram-oriented sockets that transfer messages between the kernel and
+//! the user space. Each netlink protocol (e.g., `NETLINK_ROUTE`) is a separate family of
+//! sockets, which has its own socket table and its own message types. Messages sent to the port
+//! of the kernel are handled by the protocol in the kernel (see [`kernel`]), while messages can
+//! also be sent to other sockets or to multicast groups.
+//!
+//! Reference: <https://man7.org/linux/man-pages/man7/netlink.7.html>.
+
+mod addr;
+mod generic;
+mod kernel;
+mod message;
+mod netfilter;
+mod notifier;
+mod options;
+mod receiver;
+mod route;
+mod socket;
+mod table;
+
+pub use addr::{GroupIdSet, NetlinkSocketAddr};
+pub use generic::NetlinkGenericSocket;
+pub use netfilter::NetlinkNetfilterSocket;
+pub use notifier::NetlinkNotifier;
+pub use options::{AddMembership, DropMembership};
+pub use route::NetlinkRouteSocket;
+
+use crate::prelude::*;
+
+/// The standard netlink protocols.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/netlink.h#L9>.
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, TryFromInt, PartialEq, Eq)]
+#[expect(non_camel_case_types)]
+#[expect(clippy::upper_case_acronyms)]
+pub enum StandardNetlinkProtocol {
+    /// Routing/device hook
+    ROUTE = 0,
+    /// Unused number
+    UNUSED = 1,
+    /// Reserved for user mode socket protocols
+    USERSOCK = 2,
+    /// Unused number, formerly ip_queue
+    FIREWALL = 3,
+    /// Socket monitoring
+    SOCK_DIAG = 4,
+    /// Netfilter/iptables ULOG
+    NFLOG = 5,
+    /// IPsec
+    XFRM = 6,
+    /// SELinux event notifications
+    SELINUX = 7,
+    /// Open-iSCSI
+    ISCSI = 8,
+    /// Auditing
+    AUDIT = 9,
+    FIB_LOOKUP = 10,
+    CONNECTOR = 11,
+    /// Netfilter subsystem
+    NETFILTER = 12,
+    IP6_FW = 13,
+    /// DECnet routing messages
+    DNRTMSG = 14,
+    /// Kernel messages to the user space
+    KOBJECT_UEVENT = 15,
+    GENERIC = 16,
+    /// SCSI transports
+    SCSITRANSPORT = 18,
+    ECRYPTFS = 19,
+    RDMA = 20,
+    /// Crypto layer
+    CRYPTO = 21,
+    /// SMC monitoring
+    SMC = 22,
+}
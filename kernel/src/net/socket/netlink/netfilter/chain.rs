@@ -0,0 +1,71 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Handlers of chain requests.
+
+use aster_bigtcp::filter::FilterHook;
+
+use super::{
+    hook_from_c, hook_to_c,
+    message::{CChainAttr, CFilterMsg, CFilterSegmentType},
+    verdict_from_c, verdict_to_c, ALL_HOOKS,
+};
+use crate::{
+    net::{
+        iface::FILTER_TABLE,
+        socket::netlink::{
+            kernel::{Request, Response},
+            message::{attr::AttrList, SegmentFlags, SegmentWriter},
+        },
+    },
+    prelude::*,
+    util::net::CSocketAddrFamily,
+};
+
+pub(super) fn get_chain(request: &Request) -> Result<Response> {
+    let (body, _) = request.parse_body::<CFilterMsg>()?;
+
+    if !request.is_dump() {
+        let hook = hook_from_c(body.hook)?;
+        let mut writer =
+            request.reply_writer(CFilterSegmentType::NEWCHAIN as u16, SegmentFlags::empty());
+        write_chain(&mut writer, hook);
+        return Ok(Response::Reply(writer.finish()));
+    }
+
+    let segments = ALL_HOOKS
+        .into_iter()
+        .map(|hook| {
+            let mut writer =
+                request.reply_writer(CFilterSegmentType::NEWCHAIN as u16, SegmentFlags::MULTI);
+            write_chain(&mut writer, hook);
+            writer.finish()
+        })
+        .collect();
+
+    Ok(Response::Dump(segments))
+}
+
+pub(super) fn new_chain(request: &Request) -> Result<Response> {
+    let (body, attrs) = request.parse_body::<CFilterMsg>()?;
+    let attrs = AttrList::parse(attrs)?;
+
+    // The chains always exist, so creating a chain only updates its policy.
+    let hook = hook_from_c(body.hook)?;
+    if let Some(policy) = attrs.get_u32(CChainAttr::POLICY as u16)? {
+        let policy = verdict_from_c(policy)?;
+        FILTER_TABLE.write().set_policy(hook, policy);
+    }
+
+    Ok(Response::Done)
+}
+
+fn write_chain(writer: &mut SegmentWriter, hook: FilterHook) {
+    writer.write_body(&CFilterMsg {
+        family: CSocketAddrFamily::AF_UNSPEC as u8,
+        hook: hook_to_c(hook),
+        _pad: 0,
+    });
+
+    let policy = FILTER_TABLE.read().policy(hook);
+    writer.write_attr_u32(CChainAttr::POLICY as u16, verdict_to_c(policy));
+}
@@ -0,0 +1,93 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The message format of the `NETLINK_NETFILTER` protocol.
+//!
+//! Each segment starts with [`CFilterMsg`], which selects the address family and the hook,
+//! followed by the attributes of the chain ([`CChainAttr`]) or the rule ([`CRuleAttr`]). The
+//! constants of the hooks and the verdicts are the same as Linux, but the segment types and the
+//! attributes are specific to this simplified protocol.
+
+use crate::prelude::*;
+
+/// The types of `NETLINK_NETFILTER` segments.
+#[repr(u16)]
+#[derive(Debug, Clone, Copy, TryFromInt, PartialEq, Eq)]
+#[expect(clippy::upper_case_acronyms)]
+pub(super) enum CFilterSegmentType {
+    NEWCHAIN = 16,
+    GETCHAIN = 18,
+    NEWRULE = 20,
+    DELRULE = 21,
+    GETRULE = 22,
+}
+
+/// The body of all segments.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct CFilterMsg {
+    /// The address family (`AF_UNSPEC` matches both IPv4 and IPv6 packets)
+    pub(super) family: u8,
+    /// The hook of the chain (`NF_INET_*`)
+    pub(super) hook: u8,
+    pub(super) _pad: u16,
+}
+
+/// The attributes of chain segments.
+#[repr(u16)]
+#[derive(Debug, Clone, Copy, TryFromInt, PartialEq, Eq)]
+#[expect(clippy::upper_case_acronyms)]
+pub(super) enum CChainAttr {
+    /// The policy of the chain (`NF_DROP` or `NF_ACCEPT`) as a `u32`
+    POLICY = 1,
+}
+
+/// The attributes of rule segments.
+///
+/// Criteria that are not specified match all packets.
+#[repr(u16)]
+#[derive(Debug, Clone, Copy, TryFromInt, PartialEq, Eq)]
+#[expect(non_camel_case_types)]
+#[expect(clippy::upper_case_acronyms)]
+pub(super) enum CRuleAttr {
+    /// The handle of the rule as a `u32`
+    HANDLE = 1,
+    /// The transport protocol (`IPPROTO_*`) as a `u8`
+    PROTOCOL = 2,
+    /// The source address, whose length depends on the address family
+    SRC = 3,
+    /// The prefix length of the source address as a `u8`
+    SRC_LEN = 4,
+    /// The destination address, whose length depends on the address family
+    DST = 5,
+    /// The prefix length of the destination address as a `u8`
+    DST_LEN = 6,
+    /// The range of the source ports as a [`CPortRange`]
+    SRC_PORTS = 7,
+    /// The range of the destination ports as a [`CPortRange`]
+    DST_PORTS = 8,
+    /// The verdict of the rule (`NF_DROP` or `NF_ACCEPT`) as a `u32`
+    VERDICT = 9,
+}
+
+/// An inclusive range of ports in the host byte order.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct CPortRange {
+    pub(super) min: u16,
+    pub(super) max: u16,
+}
+
+/// The hooks of IPv4 and IPv6 packets (`NF_INET_*`).
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/netfilter.h#L42>.
+pub(super) const NF_INET_PRE_ROUTING: u8 = 0;
+pub(super) const NF_INET_LOCAL_IN: u8 = 1;
+pub(super) const NF_INET_FORWARD: u8 = 2;
+pub(super) const NF_INET_LOCAL_OUT: u8 = 3;
+pub(super) const NF_INET_POST_ROUTING: u8 = 4;
+
+/// The verdicts (`NF_*`).
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/netfilter.h#L11>.
+pub(super) const NF_DROP: u32 = 0;
+pub(super) const NF_ACCEPT: u32 = 1;
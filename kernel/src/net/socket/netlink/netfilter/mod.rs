@@ -0,0 +1,107 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The `NETLINK_NETFILTER` protocol.
+//!
+//! The protocol is used to configure the stateless packet filter (see
+//! [`aster_bigtcp::filter`]), which has one chain of rules for each of the prerouting, input,
+//! and output hooks. Unlike Linux, where the protocol transfers `nf_tables` expressions, rules
+//! are described directly by their matching criteria (see [`message`]). This is enough for basic
+//! firewalling, since the filter only matches the IP version, the transport protocol, the
+//! addresses, and the ports.
+//!
+//! Reference: <https://wiki.nftables.org/wiki-nftables/index.php/Netfilter_hooks>.
+
+mod chain;
+mod message;
+mod rule;
+
+use aster_bigtcp::filter::{FilterHook, Verdict};
+
+use self::message::{
+    CFilterSegmentType, NF_ACCEPT, NF_DROP, NF_INET_FORWARD, NF_INET_LOCAL_IN, NF_INET_LOCAL_OUT,
+    NF_INET_POST_ROUTING, NF_INET_PRE_ROUTING,
+};
+use super::{
+    kernel::{check_net_admin, KernelProtocol, Request, Response},
+    socket::NetlinkSocket,
+    table::NetlinkSocketTable,
+};
+use crate::prelude::*;
+
+/// A `NETLINK_NETFILTER` socket.
+pub type NetlinkNetfilterSocket = NetlinkSocket<NetfilterProtocol>;
+
+/// The `NETLINK_NETFILTER` protocol.
+pub struct NetfilterProtocol;
+
+static NETFILTER_SOCKET_TABLE: NetlinkSocketTable = NetlinkSocketTable::new();
+
+impl KernelProtocol for NetfilterProtocol {
+    const IS_NONROOT_RECV_ALLOWED: bool = false;
+    const IS_NONROOT_SEND_ALLOWED: bool = false;
+
+    fn socket_table() -> &'static NetlinkSocketTable {
+        &NETFILTER_SOCKET_TABLE
+    }
+
+    fn handle_request(request: &Request) -> Result<Response> {
+        // Like Linux, all requests require `CAP_NET_ADMIN`, including `GET` requests.
+        check_net_admin()?;
+
+        let Ok(type_) = CFilterSegmentType::try_from(request.header.type_) else {
+            return_errno_with_message!(Errno::EOPNOTSUPP, "the request type is not supported");
+        };
+
+        match type_ {
+            CFilterSegmentType::NEWCHAIN => chain::new_chain(request),
+            CFilterSegmentType::GETCHAIN => chain::get_chain(request),
+            CFilterSegmentType::NEWRULE => rule::new_rule(request),
+            CFilterSegmentType::DELRULE => rule::del_rule(request),
+            CFilterSegmentType::GETRULE => rule::get_rule(request),
+        }
+    }
+}
+
+/// The hooks in the order of their `NF_INET_*` values.
+const ALL_HOOKS: [FilterHook; 3] = [
+    FilterHook::Prerouting,
+    FilterHook::Input,
+    FilterHook::Output,
+];
+
+fn hook_from_c(hook: u8) -> Result<FilterHook> {
+    match hook {
+        NF_INET_PRE_ROUTING => Ok(FilterHook::Prerouting),
+        NF_INET_LOCAL_IN => Ok(FilterHook::Input),
+        NF_INET_LOCAL_OUT => Ok(FilterHook::Output),
+        // Packets are never forwarded, so there are no chains for the forward hook and the
+        // postrouting hook.
+        NF_INET_FORWARD | NF_INET_POST_ROUTING => {
+            return_errno_with_message!(Errno::EOPNOTSUPP, "the hook is not supported")
+        }
+        _ => return_errno_with_message!(Errno::EINVAL, "the hook is invalid"),
+    }
+}
+
+fn hook_to_c(hook: FilterHook) -> u8 {
+    match hook {
+        FilterHook::Prerouting => NF_INET_PRE_ROUTING,
+        FilterHook::Input => NF_INET_LOCAL_IN,
+        FilterHook::Output => NF_INET_LOCAL_OUT,
+    }
+}
+
+fn verdict_from_c(verdict: u32) -> Result<Verdict> {
+    match verdict {
+        NF_DROP => Ok(Verdict::Drop),
+        NF_ACCEPT => Ok(Verdict::Accept),
+        _ => return_errno_with_message!(Errno::EINVAL, "the verdict is invalid"),
+    }
+}
+
+fn verdict_to_c(verdict: Verdict) -> u32 {
+    match verdict {
+        Verdict::Drop => NF_DROP,
+        Verdict::Accept => NF_ACCEPT,
+    }
+}
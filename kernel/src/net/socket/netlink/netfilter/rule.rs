@@ -0,0 +1,233 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Handlers of rule requests.
+
+use core::ops::RangeInclusive;
+
+use aster_bigtcp::{
+    filter::{FilterHook, FilterRule},
+    wire::{IpAddress, IpCidr, IpProtocol, IpVersion, Ipv4Address, Ipv6Address},
+};
+
+use super::{
+    hook_from_c, hook_to_c,
+    message::{CFilterMsg, CFilterSegmentType, CPortRange, CRuleAttr},
+    verdict_from_c, verdict_to_c, ALL_HOOKS,
+};
+use crate::{
+    net::{
+        iface::FILTER_TABLE,
+        socket::netlink::{
+            kernel::{Request, Response},
+            message::{attr::AttrList, SegmentFlags, SegmentWriter},
+        },
+    },
+    prelude::*,
+    util::net::CSocketAddrFamily,
+};
+
+pub(super) fn get_rule(request: &Request) -> Result<Response> {
+    if !request.is_dump() {
+        return_errno_with_message!(Errno::EOPNOTSUPP, "only dumping rules is supported");
+    }
+
+    let table = FILTER_TABLE.read();
+
+    let mut segments = Vec::new();
+    for hook in ALL_HOOKS {
+        for (handle, rule) in table.rules(hook) {
+            let mut writer =
+                request.reply_writer(CFilterSegmentType::NEWRULE as u16, SegmentFlags::MULTI);
+            write_rule(&mut writer, hook, handle, rule);
+            segments.push(writer.finish());
+        }
+    }
+
+    Ok(Response::Dump(segments))
+}
+
+pub(super) fn new_rule(request: &Request) -> Result<Response> {
+    let (body, attrs) = request.parse_body::<CFilterMsg>()?;
+    let attrs = AttrList::parse(attrs)?;
+
+    let hook = hook_from_c(body.hook)?;
+    let rule = parse_rule(&body, &attrs)?;
+
+    // Like `nft add rule`, the rule is added to the end of the chain if `NLM_F_APPEND` is
+    // specified. Otherwise, it is added to the beginning of the chain, like `nft insert rule`.
+    let flags = request.flags();
+    let handle =
+        FILTER_TABLE
+            .write()
+            .add_rule(hook, rule.clone(), flags.contains(SegmentFlags::APPEND))?;
+
+    // Like Linux, the new rule is echoed if `NLM_F_ECHO` is specified, so that the user space
+    // can learn the handle of the rule.
+    if flags.contains(SegmentFlags::ECHO) {
+        let mut writer =
+            request.reply_writer(CFilterSegmentType::NEWRULE as u16, SegmentFlags::empty());
+        write_rule(&mut writer, hook, handle, &rule);
+        return Ok(Response::Reply(writer.finish()));
+    }
+
+    Ok(Response::Done)
+}
+
+pub(super) fn del_rule(request: &Request) -> Result<Response> {
+    let (body, attrs) = request.parse_body::<CFilterMsg>()?;
+    let attrs = AttrList::parse(attrs)?;
+
+    let hook = hook_from_c(body.hook)?;
+
+    // Like `nft flush chain`, all the rules in the chain are removed if no handle is specified.
+    let Some(handle) = attrs.get_u32(CRuleAttr::HANDLE as u16)? else {
+        FILTER_TABLE.write().flush(hook);
+        return Ok(Response::Done);
+    };
+
+    FILTER_TABLE
+        .write()
+        .remove_rule(hook, handle)
+        .map_err(|_| Error::with_message(Errno::ENOENT, "the rule does not exist"))?;
+
+    Ok(Response::Done)
+}
+
+fn parse_rule(body: &CFilterMsg, attrs: &AttrList) -> Result<FilterRule> {
+    let ip_version = match CSocketAddrFamily::try_from(body.family as i32) {
+        Ok(CSocketAddrFamily::AF_UNSPEC) => None,
+        Ok(CSocketAddrFamily::AF_INET) => Some(IpVersion::Ipv4),
+        Ok(CSocketAddrFamily::AF_INET6) => Some(IpVersion::Ipv6),
+        _ => return_errno_with_message!(Errno::EAFNOSUPPORT, "the address family is not supported"),
+    };
+
+    let protocol = attrs
+        .get_u8(CRuleAttr::PROTOCOL as u16)?
+        .map(IpProtocol::from);
+
+    let src_cidr = parse_cidr(attrs, ip_version, CRuleAttr::SRC, CRuleAttr::SRC_LEN)?;
+    let dst_cidr = parse_cidr(attrs, ip_version, CRuleAttr::DST, CRuleAttr::DST_LEN)?;
+
+    let src_ports = parse_ports(attrs, CRuleAttr::SRC_PORTS)?;
+    let dst_ports = parse_ports(attrs, CRuleAttr::DST_PORTS)?;
+
+    let Some(verdict) = attrs.get_u32(CRuleAttr::VERDICT as u16)? else {
+        return_errno_with_message!(Errno::EINVAL, "the verdict is not specified");
+    };
+    let verdict = verdict_from_c(verdict)?;
+
+    Ok(FilterRule {
+        ip_version,
+        protocol,
+        src_cidr,
+        dst_cidr,
+        src_ports,
+        dst_ports,
+        verdict,
+    })
+}
+
+fn parse_cidr(
+    attrs: &AttrList,
+    ip_version: Option<IpVersion>,
+    addr_type: CRuleAttr,
+    prefix_len_type: CRuleAttr,
+) -> Result<Option<IpCidr>> {
+    let Some(payload) = attrs.get(addr_type as u16) else {
+        return Ok(None);
+    };
+
+    let addr = match (ip_version, payload.len()) {
+        (Some(IpVersion::Ipv4), 4) => {
+            IpAddress::Ipv4(Ipv4Address::from(<[u8; 4]>::try_from(payload).unwrap()))
+        }
+        (Some(IpVersion::Ipv6), 16) => {
+            IpAddress::Ipv6(Ipv6Address::from(<[u8; 16]>::try_from(payload).unwrap()))
+        }
+        _ => return_errno_with_message!(
+            Errno::EINVAL,
+            "the address length is invalid for the address family"
+        ),
+    };
+
+    let max_prefix_len = match addr {
+        IpAddress::Ipv4(_) => 32,
+        IpAddress::Ipv6(_) => 128,
+    };
+    let prefix_len = attrs
+        .get_u8(prefix_len_type as u16)?
+        .unwrap_or(max_prefix_len);
+    if prefix_len > max_prefix_len {
+        return_errno_with_message!(Errno::EINVAL, "the prefix length is invalid");
+    }
+
+    Ok(Some(IpCidr::new(addr, prefix_len)))
+}
+
+fn parse_ports(attrs: &AttrList, type_: CRuleAttr) -> Result<Option<RangeInclusive<u16>>> {
+    let Some(payload) = attrs.get(type_ as u16) else {
+        return Ok(None);
+    };
+
+    let Some(bytes) = payload.get(..size_of::<CPortRange>()) else {
+        return_errno_with_message!(Errno::EINVAL, "the attribute is too short");
+    };
+    let ports = CPortRange::from_bytes(bytes);
+    if ports.min > ports.max {
+        return_errno_with_message!(Errno::EINVAL, "the port range is empty");
+    }
+
+    Ok(Some(ports.min..=ports.max))
+}
+
+fn write_rule(writer: &mut SegmentWriter, hook: FilterHook, handle: u32, rule: &FilterRule) {
+    let family = match rule.ip_version {
+        None => CSocketAddrFamily::AF_UNSPEC,
+        Some(IpVersion::Ipv4) => CSocketAddrFamily::AF_INET,
+        Some(IpVersion::Ipv6) => CSocketAddrFamily::AF_INET6,
+    };
+    writer.write_body(&CFilterMsg {
+        family: family as u8,
+        hook: hook_to_c(hook),
+        _pad: 0,
+    });
+
+    writer.write_attr_u32(CRuleAttr::HANDLE as u16, handle);
+    if let Some(protocol) = rule.protocol {
+        writer.write_attr_u8(CRuleAttr::PROTOCOL as u16, protocol.into());
+    }
+    if let Some(cidr) = rule.src_cidr {
+        write_cidr(writer, cidr, CRuleAttr::SRC, CRuleAttr::SRC_LEN);
+    }
+    if let Some(cidr) = rule.dst_cidr {
+        write_cidr(writer, cidr, CRuleAttr::DST, CRuleAttr::DST_LEN);
+    }
+    if let Some(ports) = &rule.src_ports {
+        write_ports(writer, ports, CRuleAttr::SRC_PORTS);
+    }
+    if let Some(ports) = &rule.dst_ports {
+        write_ports(writer, ports, CRuleAttr::DST_PORTS);
+    }
+    writer.write_attr_u32(CRuleAttr::VERDICT as u16, verdict_to_c(rule.verdict));
+}
+
+fn write_cidr(
+    writer: &mut SegmentWriter,
+    cidr: IpCidr,
+    addr_type: CRuleAttr,
+    prefix_len_type: CRuleAttr,
+) {
+    match cidr.address() {
+        IpAddress::Ipv4(addr) => writer.write_attr(addr_type as u16, &addr.octets()),
+        IpAddress::Ipv6(addr) => writer.write_attr(addr_type as u16, &addr.octets()),
+    }
+    writer.write_attr_u8(prefix_len_type as u16, cidr.prefix_len());
+}
+
+fn write_ports(writer: &mut SegmentWriter, ports: &RangeInclusive<u16>, type_: CRuleAttr) {
+    let ports = CPortRange {
+        min: *ports.start(),
+        max: *ports.end(),
+    };
+    writer.write_attr(type_ as u16, ports.as_bytes());
+}
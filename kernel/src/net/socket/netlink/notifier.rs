@@ -0,0 +1,53 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{
+    receiver::{NetlinkMessage, ReceiveQueue},
+    NetlinkGenericSocket, NetlinkNetfilterSocket, NetlinkRouteSocket,
+};
+use crate::{fs::file_handle::FileLike, prelude::*};
+
+/// A notifier that delivers messages from the kernel to a netlink socket.
+///
+/// This allows the other parts of the kernel to notify the user space via the netlink sockets
+/// specified by the user. For example, `mq_notify` with `SIGEV_THREAD` sends the cookie of the
+/// notification to such a socket.
+#[derive(Clone)]
+pub struct NetlinkNotifier {
+    receive_queue: Arc<ReceiveQueue>,
+}
+
+impl NetlinkNotifier {
+    /// Creates a notifier for the netlink socket.
+    ///
+    /// This method fails with [`ENOTSOCK`] if the file is not a socket, or with [`EINVAL`] if
+    /// the socket is not a netlink socket.
+    ///
+    /// [`ENOTSOCK`]: crate::error::Errno::ENOTSOCK
+    /// [`EINVAL`]: crate::error::Errno::EINVAL
+    pub fn new(file: &dyn FileLike) -> Result<Self> {
+        file.as_socket_or_err()?;
+
+        let receive_queue = if let Some(socket) = file.downcast_ref::<NetlinkRouteSocket>() {
+            socket.receive_queue()
+        } else if let Some(socket) = file.downcast_ref::<NetlinkGenericSocket>() {
+            socket.receive_queue()
+        } else if let Some(socket) = file.downcast_ref::<NetlinkNetfilterSocket>() {
+            socket.receive_queue()
+        } else {
+            return_errno_with_message!(Errno::EINVAL, "the socket is not a netlink socket");
+        };
+
+        Ok(Self {
+            receive_queue: receive_queue.clone(),
+        })
+    }
+
+    /// Sends a message to the socket.
+    ///
+    /// Like Linux, the message is dropped if the receive queue of the socket is full.
+    pub fn notify(&self, bytes: Vec<u8>) {
+        let _ = self
+            .receive_queue
+            .push(NetlinkMessage::new_from_kernel(bytes));
+    }
+}
@@ -0,0 +1,142 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::NetlinkSocketAddr;
+use crate::{
+    events::IoEvents,
+    prelude::*,
+    process::signal::{PollHandle, Pollee},
+};
+
+/// A message in the receive queue of a netlink socket.
+pub(super) struct NetlinkMessage {
+    /// The bytes of the message, which consist of one or more segments
+    bytes: Vec<u8>,
+    /// The address of the sender, whose multicast groups are the groups the message is sent to
+    src_addr: NetlinkSocketAddr,
+}
+
+impl NetlinkMessage {
+    pub(super) fn new(bytes: Vec<u8>, src_addr: NetlinkSocketAddr) -> Self {
+        Self { bytes, src_addr }
+    }
+
+    /// Creates a message that is sent by the kernel.
+    pub(super) fn new_from_kernel(bytes: Vec<u8>) -> Self {
+        Self::new(bytes, NetlinkSocketAddr::new_kernel())
+    }
+
+    pub(super) fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub(super) fn src_addr(&self) -> NetlinkSocketAddr {
+        self.src_addr
+    }
+}
+
+/// The receive queue of a netlink socket.
+///
+/// The receive queue is shared with the socket table, so that messages can be delivered to the
+/// socket by other sockets and by the kernel.
+pub(super) struct ReceiveQueue {
+    messages: SpinLock<MessageQueue>,
+    pollee: Pollee,
+}
+
+struct MessageQueue {
+    messages: VecDeque<NetlinkMessage>,
+    total_len: usize,
+}
+
+impl ReceiveQueue {
+    pub(super) fn new() -> Self {
+        Self {
+            messages: SpinLock::new(MessageQueue {
+                messages: VecDeque::new(),
+                total_len: 0,
+            }),
+            pollee: Pollee::new(),
+        }
+    }
+
+    /// Pushes a message to the receive queue.
+    ///
+    /// Like Linux, the message is rejected with [`EAGAIN`] if the receive queue is full.
+    ///
+    /// [`EAGAIN`]: crate::error::Errno::EAGAIN
+    pub(super) fn push(&self, message: NetlinkMessage) -> Result<()> {
+        let mut queue = self.messages.lock();
+
+        if !queue.messages.is_empty() && queue.total_len + message.bytes.len() > RECV_BUF_SIZE {
+            return_errno_with_message!(Errno::EAGAIN, "the receive queue is full");
+        }
+
+        queue.total_len += message.bytes.len();
+        queue.messages.push_back(message);
+        drop(queue);
+
+        self.pollee.notify(IoEvents::IN);
+
+        Ok(())
+    }
+
+    /// Pushes a message that replies to a request to the receive queue.
+    ///
+    /// Unlike [`Self::push`], this method never fails because the socket that sends the request
+    /// expects the reply. The size of the reply is bounded by the size of the request anyway.
+    pub(super) fn push_reply(&self, message: NetlinkMessage) {
+        let mut queue = self.messages.lock();
+        queue.total_len += message.bytes.len();
+        queue.messages.push_back(message);
+        drop(queue);
+
+        self.pollee.notify(IoEvents::IN);
+    }
+
+    /// Pops a message from the receive queue.
+    ///
+    /// If `is_peek` is true, the message is cloned and left in the receive queue.
+    pub(super) fn try_pop(&self, is_peek: bool) -> Result<NetlinkMessage> {
+        let mut queue = self.messages.lock();
+
+        let message = if is_peek {
+            queue.messages.front().map(|message| NetlinkMessage {
+                bytes: message.bytes.clone(),
+                src_addr: message.src_addr,
+            })
+        } else {
+            let message = queue.messages.pop_front();
+            if let Some(message) = message.as_ref() {
+                queue.total_len -= message.bytes.len();
+            }
+            message
+        };
+        drop(queue);
+
+        if !is_peek {
+            self.pollee.invalidate();
+        }
+
+        message.ok_or_else(|| Error::with_message(Errno::EAGAIN, "the receive queue is empty"))
+    }
+
+    pub(super) fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
+        self.pollee
+            .poll_with(mask, poller, || self.check_io_events())
+    }
+
+    fn check_io_events(&self) -> IoEvents {
+        let mut events = IoEvents::OUT;
+
+        if !self.messages.lock().messages.is_empty() {
+            events |= IoEvents::IN;
+        }
+
+        events
+    }
+}
+
+/// The maximum total size of the messages in the receive queue.
+///
+/// This is the default value of `/proc/sys/net/core/rmem_default` in Linux.
+const RECV_BUF_SIZE: usize = 212992;
@@ -0,0 +1,181 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Handlers of address requests (`RTM_*ADDR`).
+
+use aster_bigtcp::{
+    iface::InterfaceFlags,
+    wire::{Ipv4Address, Ipv4Cidr, Ipv6Cidr},
+};
+
+use super::{
+    all_ifaces, find_iface_by_index,
+    message::{
+        CAddrAttr, CIfaddrMsg, CRouteGroup, CRouteSegmentType, IFA_F_PERMANENT, RT_SCOPE_HOST,
+        RT_SCOPE_LINK, RT_SCOPE_UNIVERSE,
+    },
+    notify,
+};
+use crate::{
+    net::{
+        iface::Iface,
+        socket::netlink::{
+            kernel::{Request, Response},
+            message::{attr::AttrList, SegmentFlags, SegmentWriter},
+        },
+    },
+    prelude::*,
+    util::net::CSocketAddrFamily,
+};
+
+pub(super) fn get_addr(request: &Request) -> Result<Response> {
+    // Linux does not support getting a single address for IPv4.
+    if !request.is_dump() {
+        return_errno_with_message!(Errno::EOPNOTSUPP, "only dumping addresses is supported");
+    }
+
+    let (body, _) = request.parse_body::<CIfaddrMsg>()?;
+
+    let is_unspec = body.family == CSocketAddrFamily::AF_UNSPEC as u8;
+    let mut segments = Vec::new();
+
+    // Like Linux, IPv4 addresses are dumped before IPv6 addresses.
+    if is_unspec || body.family == CSocketAddrFamily::AF_INET as u8 {
+        for iface in all_ifaces() {
+            for cidr in iface.ipv4_cidrs() {
+                let mut writer =
+                    request.reply_writer(CRouteSegmentType::NEWADDR as u16, SegmentFlags::MULTI);
+                write_addr(&mut writer, &iface, cidr);
+                segments.push(writer.finish());
+            }
+        }
+    }
+    if is_unspec || body.family == CSocketAddrFamily::AF_INET6 as u8 {
+        for iface in all_ifaces() {
+            for cidr in iface.ipv6_cidrs() {
+                let mut writer =
+                    request.reply_writer(CRouteSegmentType::NEWADDR as u16, SegmentFlags::MULTI);
+                write_ipv6_addr(&mut writer, &iface, cidr);
+                segments.push(writer.finish());
+            }
+        }
+    }
+
+    Ok(Response::Dump(segments))
+}
+
+pub(super) fn new_addr(request: &Request) -> Result<Response> {
+    let (body, attrs) = request.parse_body::<CIfaddrMsg>()?;
+    let attrs = AttrList::parse(attrs)?;
+
+    let addr = parse_addr(&body, &attrs)?;
+    if body.prefix_len > 32 {
+        return_errno_with_message!(Errno::EINVAL, "the prefix length is invalid");
+    }
+    let cidr = Ipv4Cidr::new(addr, body.prefix_len);
+
+    let iface = find_iface_by_index(body.index)?;
+
+    // Like Linux, an existing address is replaced only if `NLM_F_REPLACE` is specified.
+    //
+    // Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/net/ipv4/devinet.c#L927>.
+    let flags = request.flags();
+    if iface.ipv4_cidrs().iter().any(|old| old.address() == addr) {
+        if flags.contains(SegmentFlags::EXCL) || !flags.contains(SegmentFlags::REPLACE) {
+            return_errno_with_message!(Errno::EEXIST, "the address already exists");
+        }
+        iface.remove_ipv4_addr(addr)?;
+    }
+    iface.add_ipv4_cidr(cidr)?;
+
+    let mut writer = request.notification_writer(CRouteSegmentType::NEWADDR as u16);
+    write_addr(&mut writer, &iface, cidr);
+    notify(CRouteGroup::IPV4_IFADDR, writer.finish());
+
+    Ok(Response::Done)
+}
+
+pub(super) fn del_addr(request: &Request) -> Result<Response> {
+    let (body, attrs) = request.parse_body::<CIfaddrMsg>()?;
+    let attrs = AttrList::parse(attrs)?;
+
+    let addr = parse_addr(&body, &attrs)?;
+    let iface = find_iface_by_index(body.index)?;
+
+    // Like Linux, the prefix length must match if the address is specified by `IFA_ADDRESS`.
+    let is_prefix_checked = attrs.get(CAddrAttr::ADDRESS as u16).is_some();
+    let Some(cidr) = iface.ipv4_cidrs().into_iter().find(|cidr| {
+        cidr.address() == addr && (!is_prefix_checked || cidr.prefix_len() == body.prefix_len)
+    }) else {
+        return_errno_with_message!(Errno::EADDRNOTAVAIL, "the address does not exist");
+    };
+    iface.remove_ipv4_addr(addr)?;
+
+    let mut writer = request.notification_writer(CRouteSegmentType::DELADDR as u16);
+    write_addr(&mut writer, &iface, cidr);
+    notify(CRouteGroup::IPV4_IFADDR, writer.finish());
+
+    Ok(Response::Done)
+}
+
+/// Parses the address specified by `IFA_LOCAL` or `IFA_ADDRESS`.
+fn parse_addr(body: &CIfaddrMsg, attrs: &AttrList) -> Result<Ipv4Address> {
+    // TODO: Support adding and deleting IPv6 addresses
+    if body.family != CSocketAddrFamily::AF_INET as u8 {
+        return_errno_with_message!(Errno::EOPNOTSUPP, "the address family is not supported");
+    }
+
+    if let Some(addr) = attrs.get_ipv4_addr(CAddrAttr::LOCAL as u16)? {
+        return Ok(addr);
+    }
+    attrs
+        .get_ipv4_addr(CAddrAttr::ADDRESS as u16)?
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "the address is not specified"))
+}
+
+fn write_addr(writer: &mut SegmentWriter, iface: &Iface, cidr: Ipv4Cidr) {
+    let addr = cidr.address();
+
+    writer.write_body(&CIfaddrMsg {
+        family: CSocketAddrFamily::AF_INET as u8,
+        prefix_len: cidr.prefix_len(),
+        flags: IFA_F_PERMANENT,
+        scope: if addr.is_loopback() {
+            RT_SCOPE_HOST
+        } else {
+            RT_SCOPE_UNIVERSE
+        },
+        index: iface.index(),
+    });
+
+    writer.write_attr_ipv4_addr(CAddrAttr::ADDRESS as u16, addr);
+    writer.write_attr_ipv4_addr(CAddrAttr::LOCAL as u16, addr);
+    if iface.flags().contains(InterfaceFlags::BROADCAST) {
+        if let Some(broadcast) = cidr.broadcast() {
+            writer.write_attr_ipv4_addr(CAddrAttr::BROADCAST as u16, broadcast);
+        }
+    }
+    writer.write_attr_str(CAddrAttr::LABEL as u16, iface.name());
+    writer.write_attr_u32(CAddrAttr::FLAGS as u16, IFA_F_PERMANENT as u32);
+}
+
+fn write_ipv6_addr(writer: &mut SegmentWriter, iface: &Iface, cidr: Ipv6Cidr) {
+    let addr = cidr.address();
+
+    // TODO: Report the flags of addresses that are assigned dynamically (e.g., by SLAAC).
+    writer.write_body(&CIfaddrMsg {
+        family: CSocketAddrFamily::AF_INET6 as u8,
+        prefix_len: cidr.prefix_len(),
+        flags: IFA_F_PERMANENT,
+        scope: if addr.is_loopback() {
+            RT_SCOPE_HOST
+        } else if addr.is_unicast_link_local() {
+            RT_SCOPE_LINK
+        } else {
+            RT_SCOPE_UNIVERSE
+        },
+        index: iface.index(),
+    });
+
+    writer.write_attr(CAddrAttr::ADDRESS as u16, &addr.octets());
+    writer.write_attr_u32(CAddrAttr::FLAGS as u16, IFA_F_PERMANENT as u32);
+}
@@ -0,0 +1,333 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Handlers of link requests (`RTM_*LINK`).
+
+use alloc::format;
+
+use aster_bigtcp::{
+    iface::{InterfaceFlags, InterfaceType},
+    wire::HardwareAddress,
+};
+
+use super::{
+    all_ifaces, find_iface_by_index,
+    message::{CIfinfoMsg, CLinkAttr, CLinkInfoAttr, COperState, CRouteSegmentType},
+};
+use crate::{
+    net::{
+        iface::{self, Bridge, Iface, TunIface, WireGuard},
+        socket::netlink::{
+            kernel::{Request, Response},
+            message::{attr::AttrList, SegmentFlags, SegmentWriter},
+        },
+    },
+    prelude::*,
+};
+
+pub(super) fn get_link(request: &Request) -> Result<Response> {
+    let (body, attrs) = request.parse_body::<CIfinfoMsg>()?;
+
+    if request.is_dump() {
+        let segments = all_ifaces()
+            .iter()
+            .map(|iface| {
+                let mut writer =
+                    request.reply_writer(CRouteSegmentType::NEWLINK as u16, SegmentFlags::MULTI);
+                write_link(&mut writer, iface);
+                writer.finish()
+            })
+            .collect();
+        return Ok(Response::Dump(segments));
+    }
+
+    let attrs = AttrList::parse(attrs)?;
+    let iface = find_iface(&body, &attrs)?;
+
+    let mut writer = request.reply_writer(CRouteSegmentType::NEWLINK as u16, SegmentFlags::empty());
+    write_link(&mut writer, &iface);
+    Ok(Response::Reply(writer.finish()))
+}
+
+/// Handles `RTM_NEWLINK` and `RTM_SETLINK` requests.
+///
+/// Only bridges can be created (see [`new_link`]). For existing ifaces, only the master (i.e.,
+/// the bridge that a TAP device is a port of) can be changed. Changing other iface properties
+/// is not supported yet, so the request fails unless they are unchanged.
+pub(super) fn set_link(request: &Request) -> Result<Response> {
+    let (body, attrs) = request.parse_body::<CIfinfoMsg>()?;
+    let attrs = AttrList::parse(attrs)?;
+
+    let is_new = request.header.type_ == CRouteSegmentType::NEWLINK as u16;
+
+    let iface = match find_iface(&body, &attrs) {
+        Err(err) if is_new && err.error() == Errno::ENODEV => {
+            return new_link(request, &body, &attrs);
+        }
+        result => result?,
+    };
+
+    if is_new && request.flags().contains(SegmentFlags::EXCL) {
+        return_errno_with_message!(Errno::EEXIST, "the link already exists");
+    }
+
+    // Like Linux, the flags are changed only if they are specified.
+    //
+    // Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/net/core/rtnetlink.c#L1047>.
+    if body.flags != 0 || body.change != 0 {
+        let old_flags = iface.flags().bits();
+        let new_flags = if body.change != 0 {
+            (body.flags & body.change) | (old_flags & !body.change)
+        } else {
+            body.flags
+        };
+
+        if (old_flags ^ new_flags) & CHANGEABLE_FLAGS != 0 {
+            return_errno_with_message!(
+                Errno::EOPNOTSUPP,
+                "changing the link flags is not supported"
+            );
+        }
+    }
+
+    let mut master = None;
+    for type_ in attrs.types() {
+        match CLinkAttr::try_from(type_) {
+            Ok(CLinkAttr::IFNAME) => {
+                if attrs.get_str(type_)? != Some(iface.name()) {
+                    return_errno_with_message!(
+                        Errno::EOPNOTSUPP,
+                        "renaming links is not supported"
+                    );
+                }
+            }
+            Ok(CLinkAttr::MTU) => {
+                if attrs.get_u32(type_)? != Some(iface.mtu() as u32) {
+                    return_errno_with_message!(
+                        Errno::EOPNOTSUPP,
+                        "changing the MTU is not supported"
+                    );
+                }
+            }
+            Ok(CLinkAttr::MASTER) => master = attrs.get_u32(type_)?,
+            Ok(CLinkAttr::LINKINFO) => {
+                if parse_link_kind(&attrs)? != link_kind(&iface) {
+                    return_errno_with_message!(
+                        Errno::EOPNOTSUPP,
+                        "changing the link kind is not supported"
+                    );
+                }
+            }
+            Ok(CLinkAttr::EXT_MASK) => (),
+            _ => {
+                return_errno_with_message!(Errno::EOPNOTSUPP, "the link attribute is not supported")
+            }
+        }
+    }
+
+    if let Some(master) = master {
+        set_master(&iface, master)?;
+    }
+
+    Ok(Response::Done)
+}
+
+/// Creates an iface.
+///
+/// Only bridges and WireGuard ifaces can be created (e.g., by `ip link add type bridge` or
+/// `ip link add type wireguard`). TUN/TAP devices are created with `/dev/net/tun` instead.
+fn new_link(request: &Request, body: &CIfinfoMsg, attrs: &AttrList) -> Result<Response> {
+    if !request.flags().contains(SegmentFlags::CREATE) {
+        return_errno_with_message!(Errno::ENODEV, "the link does not exist");
+    }
+    if body.index > 0 {
+        return_errno_with_message!(
+            Errno::EOPNOTSUPP,
+            "specifying the index of new links is not supported"
+        );
+    }
+
+    for type_ in attrs.types() {
+        match CLinkAttr::try_from(type_) {
+            Ok(CLinkAttr::IFNAME | CLinkAttr::LINKINFO | CLinkAttr::EXT_MASK) => (),
+            _ => {
+                return_errno_with_message!(Errno::EOPNOTSUPP, "the link attribute is not supported")
+            }
+        }
+    }
+
+    let Some(kind) = parse_link_kind(attrs)? else {
+        return_errno_with_message!(Errno::EINVAL, "the link kind is not specified");
+    };
+    if kind != BRIDGE_KIND && kind != WIREGUARD_KIND {
+        return_errno_with_message!(Errno::EOPNOTSUPP, "the link kind is not supported");
+    }
+
+    // Like Linux, the iface is named after its kind if no name is specified.
+    //
+    // Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/net/core/rtnetlink.c>.
+    let name = match attrs.get_str(CLinkAttr::IFNAME as u16)? {
+        Some(name) => name.to_string(),
+        None => format!("{}%d", kind),
+    };
+    if kind == BRIDGE_KIND {
+        Bridge::new(&name)?;
+    } else {
+        WireGuard::new(&name)?;
+    }
+
+    Ok(Response::Done)
+}
+
+/// Handles `RTM_DELLINK` requests.
+///
+/// Only the ifaces that are created by the user space (i.e., bridges, TUN/TAP devices, and
+/// WireGuard ifaces) can be deleted.
+pub(super) fn del_link(request: &Request) -> Result<Response> {
+    let (body, attrs) = request.parse_body::<CIfinfoMsg>()?;
+    let attrs = AttrList::parse(attrs)?;
+
+    let iface = find_iface(&body, &attrs)?;
+
+    if let Some(bridge) = Bridge::find(iface.index()) {
+        bridge.remove();
+    } else if let Some(tun) = TunIface::find(iface.index()) {
+        tun.remove();
+    } else if let Some(wireguard) = WireGuard::find(iface.index()) {
+        wireguard.remove();
+    } else {
+        return_errno_with_message!(Errno::EOPNOTSUPP, "the link cannot be deleted");
+    }
+
+    Ok(Response::Done)
+}
+
+/// Makes the iface a port of the bridge whose index is `master`, or releases the iface from
+/// its bridge if `master` is zero.
+fn set_master(iface: &Arc<Iface>, master: u32) -> Result<()> {
+    let Some(tun) = TunIface::find(iface.index()) else {
+        return_errno_with_message!(
+            Errno::EOPNOTSUPP,
+            "only TAP devices can be ports of bridges"
+        );
+    };
+
+    if master == 0 {
+        return tun.set_master(None);
+    }
+
+    // Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/net/core/rtnetlink.c>.
+    if iface::find_iface_by_index(master).is_none() {
+        return_errno_with_message!(Errno::EINVAL, "the master does not exist");
+    }
+    let Some(bridge) = Bridge::find(master) else {
+        return_errno_with_message!(Errno::EOPNOTSUPP, "the master is not a bridge");
+    };
+
+    tun.set_master(Some(bridge))
+}
+
+/// Finds the iface specified by the index in the body or by the name in the attributes.
+fn find_iface(body: &CIfinfoMsg, attrs: &AttrList) -> Result<Arc<Iface>> {
+    if body.index > 0 {
+        return find_iface_by_index(body.index as u32);
+    }
+
+    let Some(name) = attrs.get_str(CLinkAttr::IFNAME as u16)? else {
+        return_errno_with_message!(Errno::EINVAL, "neither the index nor the name is specified");
+    };
+    iface::find_iface_by_name(name)
+        .ok_or_else(|| Error::with_message(Errno::ENODEV, "the iface does not exist"))
+}
+
+/// Parses the kind of the iface in the `IFLA_LINKINFO` attribute.
+fn parse_link_kind<'a>(attrs: &AttrList<'a>) -> Result<Option<&'a str>> {
+    let Some(link_info) = attrs.get(CLinkAttr::LINKINFO as u16) else {
+        return Ok(None);
+    };
+
+    AttrList::parse(link_info)?.get_str(CLinkInfoAttr::KIND as u16)
+}
+
+/// Returns the kind of the iface, which is reported in the `IFLA_LINKINFO` attribute.
+///
+/// Like Linux, both TUN and TAP devices have the kind `tun`.
+fn link_kind(iface: &Iface) -> Option<&'static str> {
+    if Bridge::find(iface.index()).is_some() {
+        Some(BRIDGE_KIND)
+    } else if TunIface::find(iface.index()).is_some() {
+        Some(TUN_KIND)
+    } else if WireGuard::find(iface.index()).is_some() {
+        Some(WIREGUARD_KIND)
+    } else {
+        None
+    }
+}
+
+const BRIDGE_KIND: &str = "bridge";
+const TUN_KIND: &str = "tun";
+const WIREGUARD_KIND: &str = "wireguard";
+
+fn write_link(writer: &mut SegmentWriter, iface: &Iface) {
+    writer.write_body(&CIfinfoMsg {
+        family: 0,
+        _pad: 0,
+        type_: iface.type_() as u16,
+        index: iface.index() as i32,
+        flags: iface.flags().bits(),
+        change: 0,
+    });
+
+    writer.write_attr_str(CLinkAttr::IFNAME as u16, iface.name());
+    writer.write_attr_u32(CLinkAttr::TXQLEN as u16, DEFAULT_TX_QUEUE_LEN);
+    writer.write_attr_u8(CLinkAttr::OPERSTATE as u16, oper_state(iface) as u8);
+    writer.write_attr_u8(CLinkAttr::LINKMODE as u16, 0);
+    writer.write_attr_u32(CLinkAttr::MTU as u16, iface.mtu() as u32);
+
+    if let Some(bridge) = TunIface::find(iface.index()).and_then(|tun| tun.master()) {
+        writer.write_attr_u32(CLinkAttr::MASTER as u16, bridge.iface().index());
+    }
+    if let Some(kind) = link_kind(iface) {
+        writer.write_nested_attr(CLinkAttr::LINKINFO as u16, |writer| {
+            writer.write_attr_str(CLinkInfoAttr::KIND as u16, kind);
+        });
+    }
+
+    // Like Linux, the loopback iface has an all-zero address, although it has no link layer.
+    let (addr, broadcast) = match iface.hardware_addr() {
+        HardwareAddress::Ethernet(addr) => (addr.0, [0xff; 6]),
+        HardwareAddress::Ip if iface.type_() == InterfaceType::Loopback => ([0; 6], [0; 6]),
+        HardwareAddress::Ip => return,
+    };
+    writer.write_attr(CLinkAttr::ADDRESS as u16, &addr);
+    writer.write_attr(CLinkAttr::BROADCAST as u16, &broadcast);
+}
+
+/// Returns the operational state of the iface.
+///
+/// Like Linux, the loopback iface reports an unknown state because it does not track its
+/// carrier state.
+fn oper_state(iface: &Iface) -> COperState {
+    if iface.type_() == InterfaceType::Loopback {
+        COperState::Unknown
+    } else if iface
+        .flags()
+        .contains(InterfaceFlags::UP | InterfaceFlags::RUNNING)
+    {
+        COperState::Up
+    } else {
+        COperState::Down
+    }
+}
+
+/// The flags that can be changed by the user space.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/net/core/dev.c#L8547>.
+const CHANGEABLE_FLAGS: u32 = InterfaceFlags::UP.bits()
+    | InterfaceFlags::NOARP.bits()
+    | InterfaceFlags::PROMISC.bits()
+    | InterfaceFlags::MULTICAST.bits();
+
+/// The default length of the transmit queue.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/if_ether.h#L37>.
+const DEFAULT_TX_QUEUE_LEN: u32 = 1000;
@@ -0,0 +1,200 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The message format of the `NETLINK_ROUTE` protocol.
+//!
+//! Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/rtnetlink.h>.
+
+use crate::prelude::*;
+
+/// The types of `NETLINK_ROUTE` segments.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/rtnetlink.h#L24>.
+#[repr(u16)]
+#[derive(Debug, Clone, Copy, TryFromInt, PartialEq, Eq)]
+#[expect(clippy::upper_case_acronyms)]
+pub(super) enum CRouteSegmentType {
+    NEWLINK = 16,
+    DELLINK = 17,
+    GETLINK = 18,
+    SETLINK = 19,
+    NEWADDR = 20,
+    DELADDR = 21,
+    GETADDR = 22,
+    NEWROUTE = 24,
+    DELROUTE = 25,
+    GETROUTE = 26,
+}
+
+/// The multicast groups of the `NETLINK_ROUTE` protocol.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/rtnetlink.h#L707>.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[expect(non_camel_case_types)]
+#[expect(clippy::upper_case_acronyms)]
+pub(super) enum CRouteGroup {
+    IPV4_IFADDR = 5,
+    IPV4_ROUTE = 7,
+}
+
+/// The body of link segments (i.e., `struct ifinfomsg` in Linux).
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/rtnetlink.h#L559>.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct CIfinfoMsg {
+    pub(super) family: u8,
+    pub(super) _pad: u8,
+    /// The hardware type (`ARPHRD_*`)
+    pub(super) type_: u16,
+    /// The index of the iface
+    pub(super) index: i32,
+    /// The flags of the iface (`IFF_*`)
+    pub(super) flags: u32,
+    /// The mask of the flags to change
+    pub(super) change: u32,
+}
+
+/// The attributes of link segments (`IFLA_*`).
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/if_link.h#L137>.
+#[repr(u16)]
+#[derive(Debug, Clone, Copy, TryFromInt, PartialEq, Eq)]
+#[expect(non_camel_case_types)]
+#[expect(clippy::upper_case_acronyms)]
+pub(super) enum CLinkAttr {
+    ADDRESS = 1,
+    BROADCAST = 2,
+    IFNAME = 3,
+    MTU = 4,
+    MASTER = 10,
+    TXQLEN = 13,
+    OPERSTATE = 16,
+    LINKMODE = 17,
+    LINKINFO = 18,
+    EXT_MASK = 29,
+}
+
+/// The nested attributes of `IFLA_LINKINFO` (`IFLA_INFO_*`).
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/if_link.h>.
+#[repr(u16)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[expect(clippy::upper_case_acronyms)]
+pub(super) enum CLinkInfoAttr {
+    KIND = 1,
+}
+
+/// The operational states of ifaces (`IF_OPER_*`).
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/if.h#L175>.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum COperState {
+    Unknown = 0,
+    Down = 2,
+    Up = 6,
+}
+
+/// The body of address segments (i.e., `struct ifaddrmsg` in Linux).
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/if_addr.h#L8>.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct CIfaddrMsg {
+    pub(super) family: u8,
+    /// The prefix length of the address
+    pub(super) prefix_len: u8,
+    /// The flags of the address (`IFA_F_*`)
+    pub(super) flags: u8,
+    /// The scope of the address (`RT_SCOPE_*`)
+    pub(super) scope: u8,
+    /// The index of the iface
+    pub(super) index: u32,
+}
+
+/// The attributes of address segments (`IFA_*`).
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/if_addr.h#L26>.
+#[repr(u16)]
+#[derive(Debug, Clone, Copy, TryFromInt, PartialEq, Eq)]
+#[expect(clippy::upper_case_acronyms)]
+pub(super) enum CAddrAttr {
+    ADDRESS = 1,
+    LOCAL = 2,
+    LABEL = 3,
+    BROADCAST = 4,
+    FLAGS = 8,
+}
+
+/// The address is permanent (i.e., not assigned dynamically).
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/if_addr.h#L50>.
+pub(super) const IFA_F_PERMANENT: u8 = 0x80;
+
+/// The body of route segments (i.e., `struct rtmsg` in Linux).
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/rtnetlink.h#L231>.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct CRtMsg {
+    pub(super) family: u8,
+    /// The prefix length of the destination
+    pub(super) dst_len: u8,
+    /// The prefix length of the source
+    pub(super) src_len: u8,
+    /// The TOS filter
+    pub(super) tos: u8,
+    /// The routing table (`RT_TABLE_*`)
+    pub(super) table: u8,
+    /// The origin of the route (`RTPROT_*`)
+    pub(super) protocol: u8,
+    /// The distance to the destination (`RT_SCOPE_*`)
+    pub(super) scope: u8,
+    /// The type of the route (`RTN_*`)
+    pub(super) type_: u8,
+    /// The flags of the route (`RTM_F_*`)
+    pub(super) flags: u32,
+}
+
+/// The attributes of route segments (`RTA_*`).
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/rtnetlink.h#L365>.
+#[repr(u16)]
+#[derive(Debug, Clone, Copy, TryFromInt, PartialEq, Eq)]
+#[expect(clippy::upper_case_acronyms)]
+pub(super) enum CRouteAttr {
+    DST = 1,
+    SRC = 2,
+    IIF = 3,
+    OIF = 4,
+    GATEWAY = 5,
+    PRIORITY = 6,
+    PREFSRC = 7,
+    TABLE = 15,
+}
+
+/// The routing tables (`RT_TABLE_*`).
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/rtnetlink.h#L349>.
+pub(super) const RT_TABLE_UNSPEC: u8 = 0;
+pub(super) const RT_TABLE_MAIN: u8 = 254;
+
+/// The origins of routes (`RTPROT_*`).
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/rtnetlink.h#L280>.
+pub(super) const RTPROT_KERNEL: u8 = 2;
+pub(super) const RTPROT_BOOT: u8 = 3;
+
+/// The scopes of addresses and routes (`RT_SCOPE_*`).
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/rtnetlink.h#L321>.
+pub(super) const RT_SCOPE_UNIVERSE: u8 = 0;
+pub(super) const RT_SCOPE_LINK: u8 = 253;
+pub(super) const RT_SCOPE_HOST: u8 = 254;
+
+/// The types of routes (`RTN_*`).
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/rtnetlink.h#L256>.
+pub(super) const RTN_UNSPEC: u8 = 0;
+pub(super) const RTN_UNICAST: u8 = 1;
@@ -0,0 +1,104 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The `NETLINK_ROUTE` protocol.
+//!
+//! The protocol is used to query and configure the network stack, including the ifaces (links),
+//! their IP addresses, and the routes.
+//!
+//! Reference: <https://man7.org/linux/man-pages/man7/rtnetlink.7.html>.
+
+mod address;
+mod link;
+mod message;
+mod routing;
+
+use aster_bigtcp::errors::ConfigError;
+
+use self::message::{CRouteGroup, CRouteSegmentType};
+use super::{
+    addr::{GroupIdSet, NetlinkSocketAddr, KERNEL_PORT},
+    kernel::{check_net_admin, KernelProtocol, Request, Response},
+    receiver::NetlinkMessage,
+    socket::NetlinkSocket,
+    table::NetlinkSocketTable,
+};
+use crate::{
+    net::iface::{self, Iface},
+    prelude::*,
+};
+
+/// A `NETLINK_ROUTE` socket.
+pub type NetlinkRouteSocket = NetlinkSocket<RouteProtocol>;
+
+/// The `NETLINK_ROUTE` protocol.
+pub struct RouteProtocol;
+
+static ROUTE_SOCKET_TABLE: NetlinkSocketTable = NetlinkSocketTable::new();
+
+impl KernelProtocol for RouteProtocol {
+    const IS_NONROOT_RECV_ALLOWED: bool = true;
+    const IS_NONROOT_SEND_ALLOWED: bool = false;
+
+    fn socket_table() -> &'static NetlinkSocketTable {
+        &ROUTE_SOCKET_TABLE
+    }
+
+    fn handle_request(request: &Request) -> Result<Response> {
+        let Ok(type_) = CRouteSegmentType::try_from(request.header.type_) else {
+            return_errno_with_message!(Errno::EOPNOTSUPP, "the request type is not supported");
+        };
+
+        // Like Linux, only `GET` requests are allowed without `CAP_NET_ADMIN`.
+        if !matches!(
+            type_,
+            CRouteSegmentType::GETLINK | CRouteSegmentType::GETADDR | CRouteSegmentType::GETROUTE
+        ) {
+            check_net_admin()?;
+        }
+
+        match type_ {
+            CRouteSegmentType::GETLINK => link::get_link(request),
+            CRouteSegmentType::NEWLINK | CRouteSegmentType::SETLINK => link::set_link(request),
+            CRouteSegmentType::DELLINK => link::del_link(request),
+            CRouteSegmentType::GETADDR => address::get_addr(request),
+            CRouteSegmentType::NEWADDR => address::new_addr(request),
+            CRouteSegmentType::DELADDR => address::del_addr(request),
+            CRouteSegmentType::GETROUTE => routing::get_route(request),
+            CRouteSegmentType::NEWROUTE => routing::new_route(request),
+            CRouteSegmentType::DELROUTE => routing::del_route(request),
+        }
+    }
+}
+
+/// Returns all the ifaces.
+fn all_ifaces() -> Vec<Arc<Iface>> {
+    iface::all_ifaces()
+}
+
+/// Finds the iface whose index is `index`.
+fn find_iface_by_index(index: u32) -> Result<Arc<Iface>> {
+    iface::find_iface_by_index(index)
+        .ok_or_else(|| Error::with_message(Errno::ENODEV, "the iface does not exist"))
+}
+
+/// Notifies the sockets that have joined the multicast group `group`.
+fn notify(group: CRouteGroup, segment: Vec<u8>) {
+    let groups = GroupIdSet::new_one(group as u32).unwrap();
+    let src_addr = NetlinkSocketAddr::new(KERNEL_PORT, groups);
+
+    ROUTE_SOCKET_TABLE.multicast(groups, KERNEL_PORT, || {
+        NetlinkMessage::new(segment.clone(), src_addr)
+    });
+}
+
+impl From<ConfigError> for Error {
+    fn from(value: ConfigError) -> Self {
+        match value {
+            ConfigError::AlreadyExists => {
+                Error::with_message(Errno::EEXIST, "the entry already exists")
+            }
+            ConfigError::NotFound => Error::with_message(Errno::ESRCH, "the entry does not exist"),
+            ConfigError::Exhausted => Error::with_message(Errno::ENOSPC, "the table is full"),
+        }
+    }
+}
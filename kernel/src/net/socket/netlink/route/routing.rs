@@ -0,0 +1,283 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Handlers of route requests (`RTM_*ROUTE`).
+
+use aster_bigtcp::{
+    iface::{IfaceRoute, InterfaceType},
+    wire::{Ipv4Address, Ipv4Cidr},
+};
+
+use super::{
+    all_ifaces, find_iface_by_index,
+    message::{
+        CRouteAttr, CRouteGroup, CRouteSegmentType, CRtMsg, RTN_UNICAST, RTN_UNSPEC, RTPROT_BOOT,
+        RTPROT_KERNEL, RT_SCOPE_LINK, RT_SCOPE_UNIVERSE, RT_TABLE_MAIN, RT_TABLE_UNSPEC,
+    },
+    notify,
+};
+use crate::{
+    net::{
+        iface::Iface,
+        socket::netlink::{
+            kernel::{Request, Response},
+            message::{attr::AttrList, SegmentFlags, SegmentWriter},
+        },
+    },
+    prelude::*,
+    util::net::CSocketAddrFamily,
+};
+
+/// A route in the main routing table.
+enum RouteEntry {
+    /// A route to a directly connected network, which is implied by an address of the iface
+    Link(Ipv4Cidr),
+    /// A route via a gateway
+    Gateway(IfaceRoute),
+}
+
+impl RouteEntry {
+    fn dst(&self) -> Ipv4Cidr {
+        match self {
+            RouteEntry::Link(cidr) => cidr.network(),
+            RouteEntry::Gateway(route) => route.cidr,
+        }
+    }
+}
+
+pub(super) fn get_route(request: &Request) -> Result<Response> {
+    // TODO: Support looking up a single route (e.g., `ip route get`)
+    if !request.is_dump() {
+        return_errno_with_message!(Errno::EOPNOTSUPP, "only dumping routes is supported");
+    }
+
+    let (body, _) = request.parse_body::<CRtMsg>()?;
+
+    // TODO: Support IPv6 routes
+    if body.family != CSocketAddrFamily::AF_UNSPEC as u8
+        && body.family != CSocketAddrFamily::AF_INET as u8
+    {
+        return Ok(Response::Dump(Vec::new()));
+    }
+
+    let mut segments = Vec::new();
+    for iface in all_ifaces() {
+        for entry in route_entries(&iface) {
+            let mut writer =
+                request.reply_writer(CRouteSegmentType::NEWROUTE as u16, SegmentFlags::MULTI);
+            write_route(&mut writer, &iface, &entry);
+            segments.push(writer.finish());
+        }
+    }
+
+    Ok(Response::Dump(segments))
+}
+
+pub(super) fn new_route(request: &Request) -> Result<Response> {
+    let (body, attrs) = request.parse_body::<CRtMsg>()?;
+    let attrs = AttrList::parse(attrs)?;
+
+    let (dst, gateway, iface) = parse_route(&body, &attrs)?;
+    let Some(gateway) = gateway else {
+        return_errno_with_message!(
+            Errno::EOPNOTSUPP,
+            "routes without gateways are not supported"
+        );
+    };
+    let iface = match iface {
+        Some(iface) => {
+            if !iface
+                .ipv4_cidrs()
+                .iter()
+                .any(|cidr| cidr.contains_addr(&gateway))
+            {
+                return_errno_with_message!(Errno::ENETUNREACH, "the gateway is unreachable");
+            }
+            iface
+        }
+        None => find_iface_by_gateway(gateway)?,
+    };
+
+    // Like Linux, an existing route is replaced only if `NLM_F_REPLACE` is specified, and a new
+    // route is created only if `NLM_F_CREATE` is specified.
+    //
+    // Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/net/ipv4/fib_trie.c#L1219>.
+    let flags = request.flags();
+    let old_route = find_gateway_route(|_, route| route.cidr == dst);
+    match old_route {
+        Some(_) if flags.contains(SegmentFlags::EXCL) || !flags.contains(SegmentFlags::REPLACE) => {
+            return_errno_with_message!(Errno::EEXIST, "the route already exists")
+        }
+        Some((old_iface, _)) => {
+            old_iface.remove_ipv4_route(dst)?;
+        }
+        None if !flags.contains(SegmentFlags::CREATE) => {
+            return_errno_with_message!(Errno::ENOENT, "the route does not exist")
+        }
+        None => (),
+    }
+
+    let route = IfaceRoute { cidr: dst, gateway };
+    iface.add_ipv4_route(route)?;
+
+    let mut writer = request.notification_writer(CRouteSegmentType::NEWROUTE as u16);
+    write_route(&mut writer, &iface, &RouteEntry::Gateway(route));
+    notify(CRouteGroup::IPV4_ROUTE, writer.finish());
+
+    Ok(Response::Done)
+}
+
+pub(super) fn del_route(request: &Request) -> Result<Response> {
+    let (body, attrs) = request.parse_body::<CRtMsg>()?;
+    let attrs = AttrList::parse(attrs)?;
+
+    let (dst, gateway, iface) = parse_route(&body, &attrs)?;
+
+    let Some((old_iface, old_route)) = find_gateway_route(|old_iface, old_route| {
+        old_route.cidr == dst
+            && gateway.is_none_or(|gateway| old_route.gateway == gateway)
+            && iface
+                .as_ref()
+                .is_none_or(|iface| Arc::ptr_eq(iface, old_iface))
+    }) else {
+        if gateway.is_none()
+            && all_ifaces().iter().any(|iface| {
+                route_entries(iface)
+                    .iter()
+                    .any(|entry| matches!(entry, RouteEntry::Link(_)) && entry.dst() == dst)
+            })
+        {
+            return_errno_with_message!(
+                Errno::EOPNOTSUPP,
+                "deleting routes to directly connected networks is not supported"
+            );
+        }
+        return_errno_with_message!(Errno::ESRCH, "the route does not exist");
+    };
+    old_iface.remove_ipv4_route(dst)?;
+
+    let mut writer = request.notification_writer(CRouteSegmentType::DELROUTE as u16);
+    write_route(&mut writer, &old_iface, &RouteEntry::Gateway(old_route));
+    notify(CRouteGroup::IPV4_ROUTE, writer.finish());
+
+    Ok(Response::Done)
+}
+
+/// Parses the destination, the gateway, and the output iface of a route.
+fn parse_route(
+    body: &CRtMsg,
+    attrs: &AttrList,
+) -> Result<(Ipv4Cidr, Option<Ipv4Address>, Option<Arc<Iface>>)> {
+    // TODO: Support IPv6 routes
+    if body.family != CSocketAddrFamily::AF_INET as u8 {
+        return_errno_with_message!(Errno::EOPNOTSUPP, "the address family is not supported");
+    }
+
+    // TODO: Support multiple routing tables and other route types
+    let table = attrs
+        .get_u32(CRouteAttr::TABLE as u16)?
+        .unwrap_or(body.table as u32);
+    if table != RT_TABLE_UNSPEC as u32 && table != RT_TABLE_MAIN as u32 {
+        return_errno_with_message!(
+            Errno::EOPNOTSUPP,
+            "only the main routing table is supported"
+        );
+    }
+    if body.type_ != RTN_UNSPEC && body.type_ != RTN_UNICAST {
+        return_errno_with_message!(Errno::EOPNOTSUPP, "only unicast routes are supported");
+    }
+
+    if body.dst_len > 32 {
+        return_errno_with_message!(Errno::EINVAL, "the prefix length is invalid");
+    }
+    let dst_addr = attrs
+        .get_ipv4_addr(CRouteAttr::DST as u16)?
+        .unwrap_or(Ipv4Address::UNSPECIFIED);
+    let dst = Ipv4Cidr::new(dst_addr, body.dst_len);
+    if dst.network() != dst {
+        return_errno_with_message!(Errno::EINVAL, "the prefix is invalid for the prefix length");
+    }
+
+    let gateway = attrs.get_ipv4_addr(CRouteAttr::GATEWAY as u16)?;
+
+    let iface = attrs
+        .get_u32(CRouteAttr::OIF as u16)?
+        .map(find_iface_by_index)
+        .transpose()?;
+
+    Ok((dst, gateway, iface))
+}
+
+/// Returns all the routes of the iface in the main routing table.
+fn route_entries(iface: &Iface) -> Vec<RouteEntry> {
+    let mut entries = Vec::new();
+
+    // Like Linux, the routes to the loopback network are in the local routing table instead of
+    // the main routing table.
+    if iface.type_() != InterfaceType::Loopback {
+        entries.extend(iface.ipv4_cidrs().into_iter().map(RouteEntry::Link));
+    }
+
+    entries.extend(iface.ipv4_routes().into_iter().map(RouteEntry::Gateway));
+
+    entries
+}
+
+/// Finds the route via a gateway that satisfies `predicate`.
+fn find_gateway_route(
+    mut predicate: impl FnMut(&Arc<Iface>, &IfaceRoute) -> bool,
+) -> Option<(Arc<Iface>, IfaceRoute)> {
+    all_ifaces().into_iter().find_map(|iface| {
+        iface
+            .ipv4_routes()
+            .into_iter()
+            .find(|route| predicate(&iface, route))
+            .map(|route| (iface, route))
+    })
+}
+
+/// Finds the iface that is directly connected to the gateway.
+fn find_iface_by_gateway(gateway: Ipv4Address) -> Result<Arc<Iface>> {
+    all_ifaces()
+        .into_iter()
+        .find(|iface| {
+            iface
+                .ipv4_cidrs()
+                .iter()
+                .any(|cidr| cidr.contains_addr(&gateway))
+        })
+        .ok_or_else(|| Error::with_message(Errno::ENETUNREACH, "the gateway is unreachable"))
+}
+
+fn write_route(writer: &mut SegmentWriter, iface: &Iface, entry: &RouteEntry) {
+    let dst = entry.dst();
+    let (protocol, scope) = match entry {
+        RouteEntry::Link(_) => (RTPROT_KERNEL, RT_SCOPE_LINK),
+        RouteEntry::Gateway(_) => (RTPROT_BOOT, RT_SCOPE_UNIVERSE),
+    };
+
+    writer.write_body(&CRtMsg {
+        family: CSocketAddrFamily::AF_INET as u8,
+        dst_len: dst.prefix_len(),
+        src_len: 0,
+        tos: 0,
+        table: RT_TABLE_MAIN,
+        protocol,
+        scope,
+        type_: RTN_UNICAST,
+        flags: 0,
+    });
+
+    writer.write_attr_u32(CRouteAttr::TABLE as u16, RT_TABLE_MAIN as u32);
+    if dst.prefix_len() > 0 {
+        writer.write_attr_ipv4_addr(CRouteAttr::DST as u16, dst.address());
+    }
+    match entry {
+        RouteEntry::Link(cidr) => {
+            writer.write_attr_ipv4_addr(CRouteAttr::PREFSRC as u16, cidr.address())
+        }
+        RouteEntry::Gateway(route) => {
+            writer.write_attr_ipv4_addr(CRouteAttr::GATEWAY as u16, route.gateway)
+        }
+    }
+    writer.write_attr_u32(CRouteAttr::OIF as u16, iface.index());
+}
@@ -0,0 +1,326 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::{
+    marker::PhantomData,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use super::{
+    addr::{GroupIdSet, NetlinkSocketAddr, KERNEL_PORT},
+    kernel::{check_net_admin, process_message, KernelProtocol},
+    options::{AddMembership, DropMembership},
+    receiver::{NetlinkMessage, ReceiveQueue},
+};
+use crate::{
+    events::IoEvents,
+    match_sock_option_ref,
+    net::socket::{
+        options::SocketOption,
+        private::SocketPrivate,
+        util::{
+            options::{SetSocketLevelOption, SocketOptionSet},
+            send_recv_flags::SendRecvFlags,
+            socket_addr::SocketAddr,
+            MessageHeader,
+        },
+        Socket,
+    },
+    prelude::*,
+    process::signal::{PollHandle, Pollable},
+    util::{MultiRead, MultiWrite},
+};
+
+/// A netlink socket whose protocol is implemented by the kernel.
+pub struct NetlinkSocket<P: KernelProtocol> {
+    inner: Mutex<Inner>,
+    receive_queue: Arc<ReceiveQueue>,
+    options: RwLock<SocketOptionSet>,
+    is_nonblocking: AtomicBool,
+    phantom: PhantomData<P>,
+}
+
+struct Inner {
+    /// The port number that the socket is bound to, if any
+    port: Option<u32>,
+    /// The multicast groups that the socket has joined
+    groups: GroupIdSet,
+    /// The default destination of the messages sent by the socket
+    remote_addr: NetlinkSocketAddr,
+}
+
+impl SetSocketLevelOption for Inner {}
+
+impl<P: KernelProtocol> NetlinkSocket<P> {
+    pub fn new(is_nonblocking: bool) -> Arc<Self> {
+        Arc::new(Self {
+            inner: Mutex::new(Inner {
+                port: None,
+                groups: GroupIdSet::new_empty(),
+                remote_addr: NetlinkSocketAddr::new_kernel(),
+            }),
+            receive_queue: Arc::new(ReceiveQueue::new()),
+            options: RwLock::new(SocketOptionSet::new_netlink()),
+            is_nonblocking: AtomicBool::new(is_nonblocking),
+            phantom: PhantomData,
+        })
+    }
+
+    /// Returns the port number that the socket is bound to.
+    ///
+    /// If the socket is not bound, it will be bound to an unused port.
+    fn bind_if_unbound(&self, inner: &mut Inner) -> Result<u32> {
+        if let Some(port) = inner.port {
+            return Ok(port);
+        }
+
+        let port = P::socket_table().bind_unused(&self.receive_queue)?;
+        inner.port = Some(port);
+
+        Ok(port)
+    }
+
+    fn set_groups(&self, inner: &mut Inner, groups: GroupIdSet) -> Result<()> {
+        if !groups.is_empty() && !P::IS_NONROOT_RECV_ALLOWED {
+            check_net_admin()?;
+        }
+
+        P::socket_table().set_groups(&self.receive_queue, groups);
+        inner.groups = groups;
+
+        Ok(())
+    }
+
+    fn try_send(&self, bytes: Vec<u8>, remote_addr: NetlinkSocketAddr) -> Result<()> {
+        let mut inner = self.inner.lock();
+        let port = self.bind_if_unbound(&mut inner)?;
+        drop(inner);
+
+        let table = P::socket_table();
+        let src_addr = NetlinkSocketAddr::new(port, GroupIdSet::new_empty());
+
+        // Like Linux, the message is sent to the multicast group first, excluding the socket
+        // that will receive the message via unicast. Only the first group is used if multiple
+        // groups are specified.
+        if let Some(group) = remote_addr.groups().first() {
+            table.multicast(group, remote_addr.port(), || {
+                NetlinkMessage::new(bytes.clone(), NetlinkSocketAddr::new(port, group))
+            });
+        }
+
+        if remote_addr.port() != KERNEL_PORT {
+            return table.unicast(remote_addr.port(), NetlinkMessage::new(bytes, src_addr));
+        }
+
+        for reply in process_message::<P>(&bytes, port) {
+            self.receive_queue
+                .push_reply(NetlinkMessage::new_from_kernel(reply));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the receive queue of the socket.
+    pub(super) fn receive_queue(&self) -> &Arc<ReceiveQueue> {
+        &self.receive_queue
+    }
+
+    fn check_remote_addr(remote_addr: &NetlinkSocketAddr) -> Result<()> {
+        if (remote_addr.port() != KERNEL_PORT || !remote_addr.groups().is_empty())
+            && !P::IS_NONROOT_SEND_ALLOWED
+        {
+            check_net_admin()?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<P: KernelProtocol> Pollable for NetlinkSocket<P> {
+    fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
+        self.receive_queue.poll(mask, poller)
+    }
+}
+
+impl<P: KernelProtocol> SocketPrivate for NetlinkSocket<P> {
+    fn is_nonblocking(&self) -> bool {
+        self.is_nonblocking.load(Ordering::Relaxed)
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) {
+        self.is_nonblocking.store(nonblocking, Ordering::Relaxed);
+    }
+}
+
+impl<P: KernelProtocol> Socket for NetlinkSocket<P> {
+    fn bind(&self, socket_addr: SocketAddr) -> Result<()> {
+        let addr = NetlinkSocketAddr::try_from(socket_addr)?;
+
+        let mut inner = self.inner.lock();
+
+        match inner.port {
+            Some(port) if port != addr.port() => {
+                return_errno_with_message!(Errno::EINVAL, "the socket is already bound")
+            }
+            Some(_) => (),
+            None if addr.port() == KERNEL_PORT => {
+                self.bind_if_unbound(&mut inner)?;
+            }
+            None => {
+                P::socket_table().bind(addr.port(), &self.receive_queue)?;
+                inner.port = Some(addr.port());
+            }
+        }
+
+        self.set_groups(&mut inner, addr.groups())
+    }
+
+    fn connect(&self, socket_addr: SocketAddr) -> Result<()> {
+        let remote_addr = NetlinkSocketAddr::try_from(socket_addr)?;
+        Self::check_remote_addr(&remote_addr)?;
+
+        let mut inner = self.inner.lock();
+        self.bind_if_unbound(&mut inner)?;
+        inner.remote_addr = remote_addr;
+
+        Ok(())
+    }
+
+    fn addr(&self) -> Result<SocketAddr> {
+        let inner = self.inner.lock();
+        let addr = NetlinkSocketAddr::new(inner.port.unwrap_or(KERNEL_PORT), inner.groups);
+
+        Ok(addr.into())
+    }
+
+    fn peer_addr(&self) -> Result<SocketAddr> {
+        Ok(self.inner.lock().remote_addr.into())
+    }
+
+    fn get_option(&self, option: &mut dyn SocketOption) -> Result<()> {
+        self.options.read().get_option(option)
+    }
+
+    fn set_option(&self, option: &dyn SocketOption) -> Result<()> {
+        let mut inner = self.inner.lock();
+
+        match_sock_option_ref!(option, {
+            add_membership: AddMembership => {
+                let group = group_from_id(*add_membership.get().unwrap())?;
+                let mut groups = inner.groups;
+                groups.add(group);
+                return self.set_groups(&mut inner, groups);
+            },
+            drop_membership: DropMembership => {
+                let group = group_from_id(*drop_membership.get().unwrap())?;
+                let mut groups = inner.groups;
+                groups.remove(group);
+                return self.set_groups(&mut inner, groups);
+            },
+            _ => ()
+        });
+
+        self.options
+            .write()
+            .set_option(option, &mut *inner)
+            .map(|_| ())
+    }
+
+    fn sendmsg(
+        &self,
+        reader: &mut dyn MultiRead,
+        message_header: MessageHeader,
+        flags: SendRecvFlags,
+    ) -> Result<usize> {
+        // TODO: Deal with flags
+        if !flags.is_all_supported() {
+            warn!("unsupported flags: {:?}", flags);
+        }
+
+        let MessageHeader {
+            addr,
+            control_messages,
+        } = message_header;
+
+        // TODO: Support control messages
+        if !control_messages.is_empty() {
+            warn!("sending control messages is not supported");
+        }
+
+        let remote_addr = match addr {
+            Some(addr) => {
+                let remote_addr = NetlinkSocketAddr::try_from(addr)?;
+                Self::check_remote_addr(&remote_addr)?;
+                remote_addr
+            }
+            None => self.inner.lock().remote_addr,
+        };
+
+        // Like Linux, the message size is limited by the send buffer.
+        let len = reader.sum_lens();
+        if len + SEND_BUF_OVERHEAD > self.options.read().send_buf() as usize {
+            return_errno_with_message!(Errno::EMSGSIZE, "the message is too large");
+        }
+
+        let mut bytes = vec![0u8; len];
+        reader.read(&mut VmWriter::from(bytes.as_mut_slice()))?;
+
+        self.try_send(bytes, remote_addr)?;
+
+        Ok(len)
+    }
+
+    fn recvmsg(
+        &self,
+        writer: &mut dyn MultiWrite,
+        flags: SendRecvFlags,
+    ) -> Result<(usize, MessageHeader)> {
+        let supported_flags =
+            SendRecvFlags::MSG_PEEK | SendRecvFlags::MSG_TRUNC | SendRecvFlags::MSG_DONTWAIT;
+        if !(flags - supported_flags).is_all_supported() {
+            warn!("unsupported flags: {:?}", flags);
+        }
+
+        let is_peek = flags.contains(SendRecvFlags::MSG_PEEK);
+        let message = if flags.contains(SendRecvFlags::MSG_DONTWAIT) {
+            self.receive_queue.try_pop(is_peek)?
+        } else {
+            self.block_on(IoEvents::IN, || self.receive_queue.try_pop(is_peek))?
+        };
+
+        let copied_len = writer.write(&mut VmReader::from(message.bytes()))?;
+
+        // Like Linux, the full length of the message is returned with `MSG_TRUNC`, which can be
+        // used to determine the required buffer size.
+        let len = if flags.contains(SendRecvFlags::MSG_TRUNC) {
+            message.bytes().len()
+        } else {
+            copied_len
+        };
+
+        let message_header = MessageHeader::new(Some(message.src_addr().into()), Vec::new());
+
+        Ok((len, message_header))
+    }
+}
+
+impl<P: KernelProtocol> Drop for NetlinkSocket<P> {
+    fn drop(&mut self) {
+        let inner = self.inner.get_mut();
+        let table = P::socket_table();
+
+        if let Some(port) = inner.port {
+            table.unbind(port);
+        }
+        table.set_groups(&self.receive_queue, GroupIdSet::new_empty());
+    }
+}
+
+fn group_from_id(group_id: u32) -> Result<GroupIdSet> {
+    GroupIdSet::new_one(group_id)
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "the multicast group is invalid"))
+}
+
+/// The size that the send buffer reserves for bookkeeping overhead.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/net/netlink/af_netlink.c#L1876>.
+const SEND_BUF_OVERHEAD: usize = 32;
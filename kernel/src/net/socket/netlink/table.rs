@@ -0,0 +1,135 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{
+    addr::{GroupIdSet, KERNEL_PORT},
+    receiver::{NetlinkMessage, ReceiveQueue},
+};
+use crate::prelude::*;
+
+/// The table of the netlink sockets of a protocol.
+///
+/// The table records the port numbers that the sockets are bound to and the multicast groups
+/// that the sockets have joined.
+pub struct NetlinkSocketTable {
+    inner: RwLock<TableInner>,
+}
+
+struct TableInner {
+    ports: BTreeMap<u32, Arc<ReceiveQueue>>,
+    members: Vec<GroupMember>,
+}
+
+struct GroupMember {
+    receive_queue: Arc<ReceiveQueue>,
+    groups: GroupIdSet,
+}
+
+impl NetlinkSocketTable {
+    pub(super) const fn new() -> Self {
+        Self {
+            inner: RwLock::new(TableInner {
+                ports: BTreeMap::new(),
+                members: Vec::new(),
+            }),
+        }
+    }
+
+    /// Binds the socket whose receive queue is `receive_queue` to the port `port`.
+    pub(super) fn bind(&self, port: u32, receive_queue: &Arc<ReceiveQueue>) -> Result<()> {
+        let mut inner = self.inner.write();
+
+        if port == KERNEL_PORT || inner.ports.contains_key(&port) {
+            return_errno_with_message!(Errno::EADDRINUSE, "the port is already in use");
+        }
+        inner.ports.insert(port, receive_queue.clone());
+
+        Ok(())
+    }
+
+    /// Binds the socket whose receive queue is `receive_queue` to an unused port.
+    ///
+    /// Like Linux, the PID of the current process is tried first. If it is already in use,
+    /// negative numbers starting from -4096 are tried one by one.
+    ///
+    /// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/net/netlink/af_netlink.c#L875>.
+    pub(super) fn bind_unused(&self, receive_queue: &Arc<ReceiveQueue>) -> Result<u32> {
+        let mut inner = self.inner.write();
+
+        let pid = current!().pid();
+        let port = core::iter::once(pid)
+            .chain((1..=AUTOBIND_START).rev())
+            .find(|port| *port != KERNEL_PORT && !inner.ports.contains_key(port))
+            .ok_or_else(|| Error::with_message(Errno::EADDRINUSE, "no port is available"))?;
+        inner.ports.insert(port, receive_queue.clone());
+
+        Ok(port)
+    }
+
+    /// Unbinds the socket from the port `port`.
+    pub(super) fn unbind(&self, port: u32) {
+        self.inner.write().ports.remove(&port);
+    }
+
+    /// Sets the multicast groups that the socket whose receive queue is `receive_queue` has
+    /// joined.
+    pub(super) fn set_groups(&self, receive_queue: &Arc<ReceiveQueue>, groups: GroupIdSet) {
+        let mut inner = self.inner.write();
+
+        let pos = inner
+            .members
+            .iter()
+            .position(|member| Arc::ptr_eq(&member.receive_queue, receive_queue));
+
+        match (pos, groups.is_empty()) {
+            (Some(pos), true) => {
+                inner.members.swap_remove(pos);
+            }
+            (Some(pos), false) => inner.members[pos].groups = groups,
+            (None, true) => (),
+            (None, false) => inner.members.push(GroupMember {
+                receive_queue: receive_queue.clone(),
+                groups,
+            }),
+        }
+    }
+
+    /// Sends a message to the socket bound to the port `port`.
+    pub(super) fn unicast(&self, port: u32, message: NetlinkMessage) -> Result<()> {
+        let inner = self.inner.read();
+
+        let Some(receive_queue) = inner.ports.get(&port) else {
+            return_errno_with_message!(
+                Errno::ECONNREFUSED,
+                "no netlink socket is bound to the port"
+            );
+        };
+        receive_queue.push(message)
+    }
+
+    /// Sends a message to all the sockets that have joined the multicast groups `groups`.
+    ///
+    /// The socket bound to the port `excluded_port` will not receive the message. Like Linux, if
+    /// the receive queue of a socket is full, the message is silently dropped for that socket.
+    pub(super) fn multicast(
+        &self,
+        groups: GroupIdSet,
+        excluded_port: u32,
+        mut build_message: impl FnMut() -> NetlinkMessage,
+    ) {
+        let inner = self.inner.read();
+
+        let excluded = inner.ports.get(&excluded_port);
+        for member in inner.members.iter() {
+            if member.groups.as_u32() & groups.as_u32() == 0
+                || excluded.is_some_and(|excluded| Arc::ptr_eq(excluded, &member.receive_queue))
+            {
+                continue;
+            }
+
+            let _ = member.receive_queue.push(build_message());
+        }
+    }
+}
+
+/// The first port number that is used for automatic binding.
+const AUTOBIND_START: u32 = -4096i32 as u32;
@@ -3,7 +3,7 @@
 use crate::{impl_socket_options, prelude::*};
 mod macros;
 
-use super::LingerOption;
+use super::{unix::UnixCredentials, LingerOption};
 
 /// Socket options. This trait represents all options that can be set or got for a socket, including
 /// socket level options and options for specific socket type like tcp socket.
@@ -20,4 +20,8 @@ impl_socket_options!(
     pub struct Error(Option<crate::error::Error>);
     pub struct Linger(LingerOption);
     pub struct KeepAlive(bool);
+    pub struct Timestamp(bool);
+    pub struct TimestampNs(bool);
+    pub struct PassCred(bool);
+    pub struct PeerCred(UnixCredentials);
 );
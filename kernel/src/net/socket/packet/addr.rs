@@ -0,0 +1,65 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use crate::{net::socket::SocketAddr, prelude::*};
+
+/// A packet socket address.
+///
+/// A packet socket address identifies an iface (by its index) and a physical-layer protocol
+/// (by its EtherType). For received frames, it also describes the source of the frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PacketSocketAddr {
+    /// The EtherType in host byte order.
+    pub protocol: u16,
+    /// The index of the iface, or zero for any iface.
+    pub ifindex: u32,
+    /// The ARP hardware type of the iface.
+    pub hatype: u16,
+    /// The packet type (see [`PacketType`]).
+    pub pkttype: u8,
+    /// The length of the physical-layer address.
+    pub halen: u8,
+    /// The physical-layer address.
+    pub addr: [u8; 8],
+}
+
+impl TryFrom<SocketAddr> for PacketSocketAddr {
+    type Error = Error;
+
+    fn try_from(value: SocketAddr) -> Result<Self> {
+        let SocketAddr::Packet(packet_addr) = value else {
+            return_errno_with_message!(Errno::EINVAL, "the address is not a packet address");
+        };
+        Ok(packet_addr)
+    }
+}
+
+impl From<PacketSocketAddr> for SocketAddr {
+    fn from(value: PacketSocketAddr) -> Self {
+        SocketAddr::Packet(value)
+    }
+}
+
+/// The types of packets, which describe how the packets are addressed.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/if_packet.h#L26>.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum PacketType {
+    /// To us
+    Host = 0,
+    /// To all
+    Broadcast = 1,
+    /// To group
+    Multicast = 2,
+    /// To someone else
+    OtherHost = 3,
+    /// Outgoing of any type
+    Outgoing = 4,
+}
+
+/// The protocol that matches every EtherType.
+pub(super) const ETH_P_ALL: u16 = 0x0003;
+/// The EtherType of IPv4 packets.
+pub(super) const ETH_P_IP: u16 = 0x0800;
+/// The EtherType of IPv6 packets.
+pub(super) const ETH_P_IPV6: u16 = 0x86DD;
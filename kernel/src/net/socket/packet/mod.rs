@@ -0,0 +1,22 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Packet sockets.
+//!
+//! Packet sockets send and receive frames at the link layer. They capture the frames that are
+//! received or transmitted by the ifaces, so tools such as `tcpdump` and DHCP clients can work
+//! with them. The captured frames can be received either by `recvmsg` or from a ring buffer that
+//! is shared with the user space (see `PACKET_RX_RING`).
+//!
+//! Reference: <https://man7.org/linux/man-pages/man7/packet.7.html>.
+
+mod addr;
+mod options;
+mod ring;
+mod socket;
+
+pub use addr::PacketSocketAddr;
+pub use options::{
+    PacketAddMembership, PacketDropMembership, PacketHdrLen, PacketMreq, PacketReserve,
+    PacketRxRing, PacketStatistics, PacketVersion, TpacketReq, TpacketStats,
+};
+pub use socket::PacketSocket;
@@ -0,0 +1,67 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use crate::{impl_socket_options, prelude::*};
+
+impl_socket_options!(
+    pub struct PacketAddMembership(PacketMreq);
+    pub struct PacketDropMembership(PacketMreq);
+    pub struct PacketRxRing(TpacketReq);
+    pub struct PacketStatistics(TpacketStats);
+    pub struct PacketVersion(u32);
+    /// The length of the frame header of a TPACKET version.
+    ///
+    /// The version is specified as the input of `getsockopt`, and the length is the output.
+    pub struct PacketHdrLen(u32);
+    pub struct PacketReserve(u32);
+);
+
+/// A request to join or leave a multicast group or the promiscuous mode.
+///
+/// See <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/if_packet.h#L287>.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub struct PacketMreq {
+    pub mr_ifindex: i32,
+    pub mr_type: u16,
+    pub mr_alen: u16,
+    pub mr_address: [u8; 8],
+}
+
+/// A request to set up a ring buffer.
+///
+/// See <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/if_packet.h#L264>.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub struct TpacketReq {
+    /// Minimal size of contiguous block
+    pub tp_block_size: u32,
+    /// Number of blocks
+    pub tp_block_nr: u32,
+    /// Size of frame
+    pub tp_frame_size: u32,
+    /// Total number of frames
+    pub tp_frame_nr: u32,
+}
+
+/// The statistics of a packet socket.
+///
+/// See <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/if_packet.h#L44>.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub struct TpacketStats {
+    /// Number of packets that are received
+    pub tp_packets: u32,
+    /// Number of packets that are dropped
+    pub tp_drops: u32,
+}
+
+/// The versions of the ring buffer.
+///
+/// See <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/if_packet.h#L258>.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, TryFromInt, PartialEq, Eq)]
+pub enum TpacketVersion {
+    V1 = 0,
+    V2 = 1,
+    V3 = 2,
+}
@@ -0,0 +1,207 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::{
+    sync::atomic::{fence, Ordering},
+    time::Duration,
+};
+
+use ostd::mm::VmIo;
+
+use super::{addr::PacketSocketAddr, options::TpacketReq};
+use crate::{
+    prelude::*,
+    util::net::CSocketAddrLl,
+    vm::vmo::{Vmo, VmoOptions},
+};
+
+/// A `PACKET_RX_RING` ring buffer (`TPACKET_V2`).
+///
+/// The ring buffer is shared with the user space by `mmap`. It consists of blocks of frames, and
+/// the ownership of each frame is transferred between the kernel and the user space by the status
+/// field in the frame header. The kernel fills the frames in order, and the user space releases a
+/// frame by setting its status back to [`TP_STATUS_KERNEL`].
+///
+/// Reference: <https://docs.kernel.org/networking/packet_mmap.html>.
+pub(super) struct RxRing {
+    vmo: Vmo,
+    block_size: usize,
+    frame_size: usize,
+    frames_per_block: usize,
+    frame_nr: usize,
+    reserve: usize,
+    /// The index of the next frame to fill.
+    head: usize,
+}
+
+/// The frame header of `TPACKET_V2`.
+///
+/// See <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/if_packet.h#L146>.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct Tpacket2Hdr {
+    tp_status: u32,
+    tp_len: u32,
+    tp_snaplen: u32,
+    tp_mac: u16,
+    tp_net: u16,
+    tp_sec: u32,
+    tp_nsec: u32,
+    tp_vlan_tci: u16,
+    tp_vlan_tpid: u16,
+    tp_padding: [u8; 4],
+}
+
+impl RxRing {
+    /// Creates a ring buffer as requested by `PACKET_RX_RING`.
+    ///
+    /// `reserve` is the number of bytes reserved before the link-layer header in each frame (see
+    /// `PACKET_RESERVE`).
+    pub(super) fn new(req: &TpacketReq, reserve: u32) -> Result<Self> {
+        let block_size = req.tp_block_size as usize;
+        let frame_size = req.tp_frame_size as usize;
+        let reserve = reserve as usize;
+
+        if block_size == 0 || block_size % PAGE_SIZE != 0 {
+            return_errno_with_message!(Errno::EINVAL, "the block size is not page-aligned");
+        }
+        if frame_size < TPACKET2_HDRLEN + reserve || frame_size % TPACKET_ALIGNMENT != 0 {
+            return_errno_with_message!(Errno::EINVAL, "the frame size is invalid");
+        }
+
+        let frames_per_block = block_size / frame_size;
+        if frames_per_block == 0 {
+            return_errno_with_message!(Errno::EINVAL, "the frame size exceeds the block size");
+        }
+        if frames_per_block * req.tp_block_nr as usize != req.tp_frame_nr as usize {
+            return_errno_with_message!(
+                Errno::EINVAL,
+                "the number of frames does not match the number of blocks"
+            );
+        }
+
+        let Some(size) = block_size.checked_mul(req.tp_block_nr as usize) else {
+            return_errno_with_message!(Errno::EINVAL, "the ring buffer is too large");
+        };
+        let vmo = VmoOptions::<Rights>::new(size).alloc()?;
+
+        Ok(Self {
+            vmo,
+            block_size,
+            frame_size,
+            frames_per_block,
+            frame_nr: req.tp_frame_nr as usize,
+            reserve,
+            head: 0,
+        })
+    }
+
+    /// Returns the VMO that backs the ring buffer.
+    pub(super) fn vmo(&self) -> &Vmo {
+        &self.vmo
+    }
+
+    /// Returns whether some frames are filled but not yet released by the user space.
+    ///
+    /// Like Linux, this only checks the last filled frame.
+    pub(super) fn has_user_frames(&self) -> bool {
+        let last = (self.head + self.frame_nr - 1) % self.frame_nr;
+        self.frame_status(last) != TP_STATUS_KERNEL
+    }
+
+    /// Fills the next frame with a received packet.
+    ///
+    /// `data` contains the packet, where the first `mac_len` bytes are the link-layer header. If
+    /// the packet does not fit in the frame, it is truncated.
+    ///
+    /// This method returns `false` if the next frame is still owned by the user space. In this
+    /// case, the packet is dropped.
+    pub(super) fn push(
+        &mut self,
+        data: &[u8],
+        mac_len: usize,
+        addr: &PacketSocketAddr,
+        timestamp: Duration,
+    ) -> bool {
+        if self.frame_status(self.head) != TP_STATUS_KERNEL {
+            return false;
+        }
+        // Do not read the frame before the user space releases it.
+        fence(Ordering::Acquire);
+
+        let net_offset = (TPACKET2_HDRLEN + mac_len.max(MIN_MAC_SPACE))
+            .next_multiple_of(TPACKET_ALIGNMENT)
+            + self.reserve;
+        let mac_offset = net_offset - mac_len;
+        let snap_len = data.len().min(self.frame_size.saturating_sub(mac_offset));
+
+        let header = Tpacket2Hdr {
+            tp_status: TP_STATUS_KERNEL,
+            tp_len: data.len() as u32,
+            tp_snaplen: snap_len as u32,
+            tp_mac: mac_offset as u16,
+            tp_net: net_offset as u16,
+            tp_sec: timestamp.as_secs() as u32,
+            tp_nsec: timestamp.subsec_nanos(),
+            tp_vlan_tci: 0,
+            tp_vlan_tpid: 0,
+            tp_padding: [0; 4],
+        };
+        let sockaddr = CSocketAddrLl::from(*addr);
+
+        let frame_offset = self.frame_offset(self.head);
+        // The VMO is allocated by the ring buffer and is large enough, so the writes cannot fail
+        // unless we are out of memory. In that case, the frame is dropped at the user side since
+        // its status remains `TP_STATUS_KERNEL`.
+        let res = self
+            .vmo
+            .write_bytes(frame_offset + mac_offset, &data[..snap_len])
+            .and_then(|_| {
+                self.vmo
+                    .write_bytes(frame_offset + SOCKADDR_OFFSET, sockaddr.as_bytes())
+            })
+            .and_then(|_| self.vmo.write_val(frame_offset, &header));
+        if res.is_err() {
+            return false;
+        }
+
+        // Publish the frame after all its contents are written.
+        fence(Ordering::Release);
+        if self.vmo.write_val(frame_offset, &TP_STATUS_USER).is_err() {
+            return false;
+        }
+
+        self.head = (self.head + 1) % self.frame_nr;
+        true
+    }
+
+    fn frame_offset(&self, index: usize) -> usize {
+        (index / self.frames_per_block) * self.block_size
+            + (index % self.frames_per_block) * self.frame_size
+    }
+
+    fn frame_status(&self, index: usize) -> u32 {
+        self.vmo
+            .read_val::<u32>(self.frame_offset(index))
+            .unwrap_or(TP_STATUS_KERNEL)
+    }
+}
+
+/// The frame is owned by the kernel.
+const TP_STATUS_KERNEL: u32 = 0;
+/// The frame is owned by the user space.
+const TP_STATUS_USER: u32 = 1;
+
+/// The alignment of the frame headers and the packets.
+const TPACKET_ALIGNMENT: usize = 16;
+
+/// The offset of the socket address in a frame.
+const SOCKADDR_OFFSET: usize = size_of::<Tpacket2Hdr>().next_multiple_of(TPACKET_ALIGNMENT);
+
+/// The length of the frame header, as reported by `PACKET_HDRLEN`.
+pub(super) const TPACKET2_HDR_LEN: usize = size_of::<Tpacket2Hdr>();
+
+/// The length of the frame header, including the socket address.
+const TPACKET2_HDRLEN: usize = SOCKADDR_OFFSET + size_of::<CSocketAddrLl>();
+
+/// The minimum space that is left for the link-layer header.
+const MIN_MAC_SPACE: usize = 16;
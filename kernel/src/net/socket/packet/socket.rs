@@ -0,0 +1,694 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::borrow::Cow;
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use aster_bigtcp::{
+    errors::link::SendError,
+    socket::{FrameDirection, LinkFilter},
+    wire::{HardwareAddress, IpVersion},
+};
+use ostd::sync::PreemptDisabled;
+
+use super::{
+    addr::{PacketSocketAddr, PacketType, ETH_P_ALL, ETH_P_IP, ETH_P_IPV6},
+    options::{
+        PacketAddMembership, PacketDropMembership, PacketHdrLen, PacketReserve, PacketRxRing,
+        PacketStatistics, PacketVersion, TpacketReq, TpacketStats, TpacketVersion,
+    },
+    ring::{RxRing, TPACKET2_HDR_LEN},
+};
+use crate::{
+    events::IoEvents,
+    match_sock_option_mut, match_sock_option_ref,
+    net::{
+        iface::{all_ifaces, Iface, LinkSocket},
+        socket::{
+            ip::datagram::DatagramObserver,
+            options::{Error as SocketError, SocketOption},
+            private::SocketPrivate,
+            util::{
+                options::{SetSocketLevelOption, SocketOptionSet},
+                send_recv_flags::SendRecvFlags,
+                socket_addr::SocketAddr,
+                MessageHeader,
+            },
+            Socket,
+        },
+    },
+    prelude::*,
+    process::{
+        credentials::capabilities::CapSet,
+        posix_thread::AsPosixThread,
+        signal::{PollHandle, Pollable, Pollee},
+    },
+    time::clocks::RealTimeClock,
+    util::{MultiRead, MultiWrite},
+    vm::vmo::Vmo,
+};
+
+#[derive(Debug, Clone)]
+struct OptionSet {
+    socket: SocketOptionSet,
+    version: TpacketVersion,
+    reserve: u32,
+}
+
+impl OptionSet {
+    fn new() -> Self {
+        Self {
+            socket: SocketOptionSet::new_packet(),
+            version: TpacketVersion::V1,
+            reserve: 0,
+        }
+    }
+}
+
+/// The kind of a [`PacketSocket`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    /// A socket (`SOCK_RAW`) that sends and receives frames including the link-layer headers.
+    Raw,
+    /// A socket (`SOCK_DGRAM`) that sends and receives frames without the link-layer headers.
+    Dgram,
+}
+
+/// A packet socket.
+///
+/// Like raw IP sockets, a link-layer socket is created on each iface to capture frames. Frames
+/// from the ifaces other than the bound iface are filtered out by the link-layer sockets.
+pub struct PacketSocket {
+    kind: Kind,
+    options: RwLock<OptionSet>,
+    inner: RwLock<Inner, PreemptDisabled>,
+    sockets: Vec<LinkSocket>,
+    ring: Mutex<Option<RxRing>>,
+    num_packets: AtomicU32,
+    num_drops: AtomicU32,
+    is_nonblocking: AtomicBool,
+    pollee: Pollee,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Inner {
+    /// The EtherType of the frames to capture, in host byte order.
+    protocol: u16,
+    /// The index of the bound iface, or zero if the socket is not bound to any iface.
+    ifindex: u32,
+}
+
+impl Inner {
+    fn filter_for(&self, iface: &Iface) -> LinkFilter {
+        if self.ifindex != 0 && self.ifindex != iface.index() {
+            return LinkFilter::None;
+        }
+
+        match self.protocol {
+            0 => LinkFilter::None,
+            ETH_P_ALL => LinkFilter::All,
+            protocol => LinkFilter::EtherType(protocol),
+        }
+    }
+}
+
+/// A frame that is received by a [`PacketSocket`].
+struct RecvFrame<'a> {
+    /// The data to receive, which may or may not include the link-layer header.
+    data: Cow<'a, [u8]>,
+    /// The length of the link-layer header in the data.
+    mac_len: usize,
+    /// The address that describes the source of the frame.
+    addr: PacketSocketAddr,
+}
+
+impl PacketSocket {
+    /// Creates a packet socket (i.e., an `AF_PACKET` socket) of the `SOCK_RAW` type.
+    ///
+    /// `protocol` is the EtherType of the frames to capture, in host byte order.
+    ///
+    /// Like Linux, this method fails with `EPERM` if the current thread does not have
+    /// `CAP_NET_RAW`.
+    pub fn new_raw(is_nonblocking: bool, protocol: u16) -> Result<Arc<Self>> {
+        Self::new(is_nonblocking, Kind::Raw, protocol)
+    }
+
+    /// Creates a packet socket (i.e., an `AF_PACKET` socket) of the `SOCK_DGRAM` type.
+    ///
+    /// See [`Self::new_raw`] for details about the parameters and the errors.
+    pub fn new_dgram(is_nonblocking: bool, protocol: u16) -> Result<Arc<Self>> {
+        Self::new(is_nonblocking, Kind::Dgram, protocol)
+    }
+
+    fn new(is_nonblocking: bool, kind: Kind, protocol: u16) -> Result<Arc<Self>> {
+        let current = current_thread!();
+        let credentials = current.as_posix_thread().unwrap().credentials();
+        if !credentials.effective_capset().contains(CapSet::NET_RAW) {
+            return_errno_with_message!(Errno::EPERM, "`CAP_NET_RAW` is required");
+        }
+
+        let inner = Inner {
+            protocol,
+            ifindex: 0,
+        };
+
+        let pollee = Pollee::new();
+        let sockets = all_ifaces()
+            .iter()
+            .map(|iface| {
+                LinkSocket::new(
+                    iface.clone(),
+                    inner.filter_for(iface.as_ref()),
+                    DatagramObserver::new(pollee.clone()),
+                )
+            })
+            .collect();
+
+        Ok(Arc::new(Self {
+            kind,
+            options: RwLock::new(OptionSet::new()),
+            inner: RwLock::new(inner),
+            sockets,
+            ring: Mutex::new(None),
+            num_packets: AtomicU32::new(0),
+            num_drops: AtomicU32::new(0),
+            is_nonblocking: AtomicBool::new(is_nonblocking),
+            pollee,
+        }))
+    }
+
+    fn find_socket(&self, ifindex: u32) -> Option<&LinkSocket> {
+        self.sockets
+            .iter()
+            .find(|socket| socket.iface().index() == ifindex)
+    }
+
+    fn try_recv(
+        &self,
+        writer: &mut dyn MultiWrite,
+        _flags: SendRecvFlags,
+    ) -> Result<(usize, SocketAddr)> {
+        let inner = *self.inner.read();
+
+        let mut result = None;
+        'outer: for socket in self.sockets.iter() {
+            loop {
+                let accepted = socket.recv(|frame, direction| {
+                    let frame = self.accept_frame(&inner, socket, frame, direction)?;
+                    Some((writer.write(&mut VmReader::from(&*frame.data)), frame.addr))
+                });
+                match accepted {
+                    // The receive queue is empty.
+                    None => break,
+                    // The frame is not for this socket.
+                    Some(None) => continue,
+                    Some(Some(accepted)) => {
+                        result = Some(accepted);
+                        break 'outer;
+                    }
+                }
+            }
+        }
+
+        // Frames may be dropped even if no frames are accepted, so always invalidate the events.
+        self.pollee.invalidate();
+
+        let Some((copied_res, addr)) = result else {
+            return_errno_with_message!(Errno::EAGAIN, "the receive buffer is empty");
+        };
+        self.num_packets.fetch_add(1, Ordering::Relaxed);
+        Ok((copied_res?, addr.into()))
+    }
+
+    /// Moves the captured frames to the ring buffer.
+    fn fill_ring(&self, ring: &mut RxRing) {
+        let inner = *self.inner.read();
+
+        for socket in self.sockets.iter() {
+            while let Some(accepted) = socket.recv(|frame, direction| {
+                let frame = self.accept_frame(&inner, socket, frame, direction)?;
+                let timestamp = RealTimeClock::get().read_time();
+                Some(ring.push(&frame.data, frame.mac_len, &frame.addr, timestamp))
+            }) {
+                match accepted {
+                    // The frame is not for this socket.
+                    None => (),
+                    Some(true) => {
+                        self.num_packets.fetch_add(1, Ordering::Relaxed);
+                    }
+                    // The ring buffer is full.
+                    Some(false) => {
+                        self.num_drops.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Checks whether the captured frame should be accepted by the socket.
+    ///
+    /// If so, this method returns the data to receive and the source address.
+    fn accept_frame<'a>(
+        &self,
+        inner: &Inner,
+        socket: &LinkSocket,
+        frame: &'a [u8],
+        direction: FrameDirection,
+    ) -> Option<RecvFrame<'a>> {
+        let iface = socket.iface();
+
+        // The filter may have been changed after the frame is captured.
+        if inner.filter_for(iface.as_ref()) == LinkFilter::None {
+            return None;
+        }
+
+        let (header, payload, local_addr) = match iface.hardware_addr() {
+            HardwareAddress::Ethernet(local_addr) => {
+                if frame.len() < ETHER_HEADER_LEN {
+                    return None;
+                }
+                let (header, payload) = frame.split_at(ETHER_HEADER_LEN);
+                (Cow::Borrowed(header), payload, Some(local_addr.0))
+            }
+            // Like Linux, frames on the loopback iface have all-zero Ethernet addresses. So we can
+            // fake Ethernet headers for them.
+            _ => {
+                let ether_type = match IpVersion::of_packet(frame).ok()? {
+                    IpVersion::Ipv4 => ETH_P_IP,
+                    IpVersion::Ipv6 => ETH_P_IPV6,
+                };
+                let mut header = [0; ETHER_HEADER_LEN];
+                header[12..].copy_from_slice(&ether_type.to_be_bytes());
+                (Cow::Owned(header.to_vec()), frame, None)
+            }
+        };
+
+        let dst_addr: [u8; 6] = header[0..6].try_into().unwrap();
+        let src_addr: [u8; 6] = header[6..12].try_into().unwrap();
+        let ether_type = u16::from_be_bytes([header[12], header[13]]);
+
+        if inner.protocol != ETH_P_ALL && inner.protocol != ether_type {
+            return None;
+        }
+
+        let pkttype = match direction {
+            FrameDirection::Outgoing => PacketType::Outgoing,
+            FrameDirection::Incoming => match local_addr {
+                None => PacketType::Host,
+                Some(local_addr) if dst_addr == local_addr => PacketType::Host,
+                Some(_) if dst_addr == [0xff; 6] => PacketType::Broadcast,
+                Some(_) if dst_addr[0] & 1 != 0 => PacketType::Multicast,
+                Some(_) => PacketType::OtherHost,
+            },
+        };
+
+        let mut addr = PacketSocketAddr {
+            protocol: ether_type,
+            ifindex: iface.index(),
+            hatype: iface.type_() as u16,
+            pkttype: pkttype as u8,
+            halen: src_addr.len() as u8,
+            addr: [0; 8],
+        };
+        addr.addr[..6].copy_from_slice(&src_addr);
+
+        let (data, mac_len) = match (self.kind, header) {
+            (Kind::Raw, Cow::Borrowed(_)) => (Cow::Borrowed(frame), ETHER_HEADER_LEN),
+            (Kind::Raw, Cow::Owned(mut header)) => {
+                header.extend_from_slice(payload);
+                (Cow::Owned(header), ETHER_HEADER_LEN)
+            }
+            (Kind::Dgram, _) => (Cow::Borrowed(payload), 0),
+        };
+
+        Some(RecvFrame {
+            data,
+            mac_len,
+            addr,
+        })
+    }
+
+    fn try_send(
+        &self,
+        reader: &mut dyn MultiRead,
+        remote: Option<PacketSocketAddr>,
+        _flags: SendRecvFlags,
+    ) -> Result<usize> {
+        let len = reader.sum_lens();
+        if len > MAX_MESSAGE_LEN {
+            return_errno_with_message!(Errno::EMSGSIZE, "the message is too large");
+        }
+
+        let mut bytes = vec![0u8; len];
+        reader.read(&mut VmWriter::from(bytes.as_mut_slice()))?;
+
+        let inner = *self.inner.read();
+        let (ifindex, protocol, dst_addr) = match remote {
+            Some(remote) => (remote.ifindex, remote.protocol, Some(remote.addr)),
+            None => (inner.ifindex, inner.protocol, None),
+        };
+
+        let Some(socket) = self.find_socket(ifindex) else {
+            return_errno_with_message!(Errno::ENXIO, "the iface does not exist");
+        };
+        let iface = socket.iface();
+
+        let frame = match (self.kind, iface.hardware_addr()) {
+            (Kind::Raw, HardwareAddress::Ethernet(_)) => bytes,
+            (Kind::Raw, _) => {
+                // The loopback iface does not really have Ethernet headers, so strip them.
+                if bytes.len() < ETHER_HEADER_LEN {
+                    return_errno_with_message!(Errno::EINVAL, "the frame is too short");
+                }
+                bytes.split_off(ETHER_HEADER_LEN)
+            }
+            (Kind::Dgram, HardwareAddress::Ethernet(local_addr)) => {
+                let mut frame = Vec::with_capacity(ETHER_HEADER_LEN + bytes.len());
+                frame.extend_from_slice(&dst_addr.unwrap_or_default()[..6]);
+                frame.extend_from_slice(&local_addr.0);
+                frame.extend_from_slice(&protocol.to_be_bytes());
+                frame.extend_from_slice(&bytes);
+                frame
+            }
+            (Kind::Dgram, _) => bytes,
+        };
+
+        match socket.send(frame) {
+            Ok(()) => (),
+            Err(SendError::TooShort) => {
+                return_errno_with_message!(Errno::EINVAL, "the frame is too short");
+            }
+            Err(SendError::BufferFull) => {
+                return_errno_with_message!(Errno::EAGAIN, "the send buffer is full");
+            }
+            Err(SendError::TooLarge) => {
+                return_errno_with_message!(Errno::EMSGSIZE, "the frame is too large");
+            }
+        }
+
+        self.pollee.invalidate();
+        iface.poll();
+
+        Ok(len)
+    }
+
+    fn check_io_events(&self) -> IoEvents {
+        let mut events = IoEvents::empty();
+
+        let mut ring = self.ring.lock();
+        if let Some(ring) = ring.as_mut() {
+            self.fill_ring(ring);
+            if ring.has_user_frames() {
+                events |= IoEvents::IN;
+            }
+        } else if self.sockets.iter().any(|socket| socket.can_recv()) {
+            events |= IoEvents::IN;
+        }
+        drop(ring);
+
+        if self.sockets.iter().all(|socket| socket.can_send()) {
+            events |= IoEvents::OUT;
+        }
+
+        events
+    }
+
+    fn set_rx_ring(&self, req: &TpacketReq) -> Result<()> {
+        let mut ring = self.ring.lock();
+
+        if req.tp_block_nr == 0 {
+            if req.tp_frame_nr != 0 {
+                return_errno_with_message!(Errno::EINVAL, "the number of frames is not zero");
+            }
+            // TODO: Like Linux, fail with `EBUSY` if the ring buffer is still mapped.
+            *ring = None;
+            return Ok(());
+        }
+
+        if ring.is_some() {
+            return_errno_with_message!(Errno::EBUSY, "the ring buffer already exists");
+        }
+
+        let (version, reserve) = {
+            let options = self.options.read();
+            (options.version, options.reserve)
+        };
+        if version != TpacketVersion::V2 {
+            return_errno_with_message!(Errno::EINVAL, "only `TPACKET_V2` rings are supported");
+        }
+
+        *ring = Some(RxRing::new(req, reserve)?);
+        drop(ring);
+
+        // Frames that are captured before the ring buffer is set up should be moved to it.
+        self.pollee.invalidate();
+
+        Ok(())
+    }
+
+    /// Updates an option that affects the layout of the ring buffer.
+    ///
+    /// Like Linux, such options cannot be changed after the ring buffer is set up.
+    fn update_ring_option<F>(&self, update: F) -> Result<()>
+    where
+        F: FnOnce(&mut OptionSet),
+    {
+        let ring = self.ring.lock();
+        if ring.is_some() {
+            return_errno_with_message!(Errno::EBUSY, "the ring buffer already exists");
+        }
+
+        update(&mut self.options.write());
+
+        Ok(())
+    }
+}
+
+impl Pollable for PacketSocket {
+    fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
+        // The user space releases the frames in the ring buffer without any system calls, so the
+        // cached events may be stale.
+        if self.ring.lock().is_some() {
+            self.pollee.invalidate();
+        }
+
+        self.pollee
+            .poll_with(mask, poller, || self.check_io_events())
+    }
+}
+
+impl SocketPrivate for PacketSocket {
+    fn is_nonblocking(&self) -> bool {
+        self.is_nonblocking.load(Ordering::Relaxed)
+    }
+
+    fn set_nonblocking(&self, is_nonblocking: bool) {
+        self.is_nonblocking.store(is_nonblocking, Ordering::Relaxed);
+    }
+}
+
+impl Socket for PacketSocket {
+    fn bind(&self, socket_addr: SocketAddr) -> Result<()> {
+        let addr = PacketSocketAddr::try_from(socket_addr)?;
+
+        if addr.ifindex != 0 && self.find_socket(addr.ifindex).is_none() {
+            return_errno_with_message!(Errno::ENODEV, "the iface does not exist");
+        }
+
+        let mut inner = self.inner.write();
+
+        // Like Linux, a zero protocol means that the protocol is not changed.
+        if addr.protocol != 0 {
+            inner.protocol = addr.protocol;
+        }
+        inner.ifindex = addr.ifindex;
+
+        for socket in self.sockets.iter() {
+            socket.set_filter(inner.filter_for(socket.iface().as_ref()));
+        }
+
+        Ok(())
+    }
+
+    fn addr(&self) -> Result<SocketAddr> {
+        let inner = *self.inner.read();
+
+        let mut addr = PacketSocketAddr {
+            protocol: inner.protocol,
+            ifindex: inner.ifindex,
+            ..Default::default()
+        };
+        if let Some(socket) = self.find_socket(inner.ifindex) {
+            let iface = socket.iface();
+            addr.hatype = iface.type_() as u16;
+            // Like Linux, the loopback iface has an all-zero address.
+            addr.halen = 6;
+            if let HardwareAddress::Ethernet(local_addr) = iface.hardware_addr() {
+                addr.addr[..6].copy_from_slice(&local_addr.0);
+            }
+        }
+
+        Ok(addr.into())
+    }
+
+    fn sendmsg(
+        &self,
+        reader: &mut dyn MultiRead,
+        message_header: MessageHeader,
+        flags: SendRecvFlags,
+    ) -> Result<usize> {
+        // TODO: Deal with flags
+        if !flags.is_all_supported() {
+            warn!("unsupported flags: {:?}", flags);
+        }
+
+        let MessageHeader {
+            addr,
+            control_messages,
+        } = message_header;
+
+        let remote = match addr {
+            Some(addr) => Some(PacketSocketAddr::try_from(addr)?),
+            None => None,
+        };
+
+        if !control_messages.is_empty() {
+            // TODO: Support sending control message
+            warn!("sending control message is not supported");
+        }
+
+        // TODO: Block if the send buffer is full
+        self.try_send(reader, remote, flags)
+    }
+
+    fn recvmsg(
+        &self,
+        writer: &mut dyn MultiWrite,
+        flags: SendRecvFlags,
+    ) -> Result<(usize, MessageHeader)> {
+        // TODO: Deal with flags
+        if !flags.is_all_supported() {
+            warn!("unsupported flags: {:?}", flags);
+        }
+
+        let (received_bytes, peer_addr) =
+            self.block_on(IoEvents::IN, || self.try_recv(writer, flags))?;
+
+        // TODO: Receive other control messages
+        let control_messages = self
+            .options
+            .read()
+            .socket
+            .timestamp_message()
+            .into_iter()
+            .collect();
+
+        let message_header = MessageHeader::new(Some(peer_addr), control_messages);
+
+        Ok((received_bytes, message_header))
+    }
+
+    fn get_option(&self, option: &mut dyn SocketOption) -> Result<()> {
+        let options = self.options.read();
+
+        match_sock_option_mut!(option, {
+            socket_errors: SocketError => {
+                // TODO: Support socket errors for packet sockets
+                socket_errors.set(None);
+                return Ok(());
+            },
+            statistics: PacketStatistics => {
+                // Like Linux, the statistics are reset after being read, and the number of
+                // packets includes the dropped packets.
+                let tp_drops = self.num_drops.swap(0, Ordering::Relaxed);
+                let tp_packets = self.num_packets.swap(0, Ordering::Relaxed) + tp_drops;
+                statistics.set(TpacketStats { tp_packets, tp_drops });
+                return Ok(());
+            },
+            version: PacketVersion => {
+                version.set(options.version as u32);
+                return Ok(());
+            },
+            hdr_len: PacketHdrLen => {
+                let version = TpacketVersion::try_from(*hdr_len.get().unwrap())
+                    .map_err(|_| Error::with_message(Errno::EINVAL, "the version is invalid"))?;
+                if version != TpacketVersion::V2 {
+                    return_errno_with_message!(
+                        Errno::EINVAL,
+                        "only `TPACKET_V2` rings are supported"
+                    );
+                }
+                hdr_len.set(TPACKET2_HDR_LEN as u32);
+                return Ok(());
+            },
+            reserve: PacketReserve => {
+                reserve.set(options.reserve);
+                return Ok(());
+            },
+            _ => ()
+        });
+
+        // Deal with socket-level options
+        options.socket.get_option(option)
+    }
+
+    fn set_option(&self, option: &dyn SocketOption) -> Result<()> {
+        match_sock_option_ref!(option, {
+            add_membership: PacketAddMembership => {
+                // TODO: Support the promiscuous mode and multicast groups
+                let ifindex = add_membership.get().unwrap().mr_ifindex as u32;
+                if self.find_socket(ifindex).is_none() {
+                    return_errno_with_message!(Errno::ENODEV, "the iface does not exist");
+                }
+                warn!("joining groups of packet sockets is not supported");
+                return Ok(());
+            },
+            drop_membership: PacketDropMembership => {
+                let ifindex = drop_membership.get().unwrap().mr_ifindex as u32;
+                if self.find_socket(ifindex).is_none() {
+                    return_errno_with_message!(Errno::ENODEV, "the iface does not exist");
+                }
+                return Ok(());
+            },
+            rx_ring: PacketRxRing => {
+                return self.set_rx_ring(rx_ring.get().unwrap());
+            },
+            version: PacketVersion => {
+                let version = TpacketVersion::try_from(*version.get().unwrap())
+                    .map_err(|_| Error::with_message(Errno::EINVAL, "the version is invalid"))?;
+                if version == TpacketVersion::V3 {
+                    return_errno_with_message!(Errno::EINVAL, "`TPACKET_V3` is not supported");
+                }
+                return self.update_ring_option(|options| options.version = version);
+            },
+            reserve: PacketReserve => {
+                let reserve = *reserve.get().unwrap();
+                return self.update_ring_option(|options| options.reserve = reserve);
+            },
+            _ => ()
+        });
+
+        // Deal with socket-level options
+        let mut kind = self.kind;
+        self.options.write().socket.set_option(option, &mut kind)?;
+
+        // Packet sockets have no states in the iface that depend on the options, so there is no
+        // need to poll the iface.
+        Ok(())
+    }
+
+    fn mmap_vmo(&self) -> Result<Vmo> {
+        let ring = self.ring.lock();
+        let Some(ring) = ring.as_ref() else {
+            return_errno_with_message!(Errno::EINVAL, "the ring buffer does not exist");
+        };
+        ring.vmo().dup()
+    }
+}
+
+impl SetSocketLevelOption for Kind {}
+
+/// The length of Ethernet headers.
+const ETHER_HEADER_LEN: usize = 14;
+
+/// The maximum length of a message that can be sent.
+const MAX_MESSAGE_LEN: usize = 0xffff;
@@ -0,0 +1,81 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{cred::UnixCredentials, gc::InflightFiles};
+use crate::{fs::file_handle::FileLike, net::socket::ControlMessage, prelude::*};
+
+/// The ancillary data that is passed along with the data via UNIX domain sockets.
+#[derive(Default)]
+pub(super) struct UnixAuxData {
+    files: Option<InflightFiles>,
+    cred: Option<UnixCredentials>,
+}
+
+impl UnixAuxData {
+    /// Creates the ancillary data from the control messages to send.
+    pub(super) fn from_control_messages(control_messages: Vec<ControlMessage>) -> Result<Self> {
+        let mut files = Vec::new();
+        let mut cred = None;
+
+        for control_message in control_messages {
+            match control_message {
+                ControlMessage::Rights(rights) => files.extend(rights),
+                ControlMessage::Credentials(credentials) => cred = Some(credentials),
+                _ => return_errno_with_message!(
+                    Errno::EINVAL,
+                    "the control message cannot be sent via UNIX domain sockets"
+                ),
+            }
+        }
+
+        let files = if files.is_empty() {
+            None
+        } else {
+            Some(InflightFiles::new(files))
+        };
+
+        Ok(Self { files, cred })
+    }
+
+    /// Returns whether the ancillary data contains nothing.
+    pub(super) fn is_empty(&self) -> bool {
+        self.files.is_none() && self.cred.is_none()
+    }
+
+    /// Attaches the credentials of the current process if no credentials are present.
+    pub(super) fn attach_current_cred(&mut self) {
+        if self.cred.is_none() {
+            self.cred = Some(UnixCredentials::new_current());
+        }
+    }
+
+    /// Visits the in-flight files.
+    pub(super) fn for_each_inflight(&self, f: &mut dyn FnMut(&Arc<dyn FileLike>)) {
+        if let Some(files) = self.files.as_ref() {
+            files.files().iter().for_each(f);
+        }
+    }
+
+    /// Takes the in-flight files out.
+    pub(super) fn take_inflight(&mut self) -> Option<InflightFiles> {
+        self.files.take()
+    }
+
+    /// Converts the ancillary data to the control messages to receive.
+    ///
+    /// If `is_pass_cred` is true (i.e., `SO_PASSCRED` is set), the credentials are always
+    /// reported, even if the sender did not attach them.
+    pub(super) fn into_control_messages(self, is_pass_cred: bool) -> Vec<ControlMessage> {
+        let mut control_messages = Vec::new();
+
+        if is_pass_cred {
+            let cred = self.cred.unwrap_or(UnixCredentials::new_invalid());
+            control_messages.push(ControlMessage::Credentials(cred));
+        }
+
+        if let Some(files) = self.files {
+            control_messages.push(ControlMessage::Rights(files.into_files()));
+        }
+
+        control_messages
+    }
+}
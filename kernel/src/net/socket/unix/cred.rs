@@ -0,0 +1,73 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use crate::{
+    prelude::*,
+    process::{credentials::capabilities::CapSet, posix_thread::AsPosixThread, Gid, Pid, Uid},
+};
+
+/// The credentials of a process that are passed via UNIX domain sockets.
+///
+/// The layout is the same as `struct ucred` in Linux, so it can be exchanged with the user space
+/// directly (e.g., via `SO_PEERCRED` or `SCM_CREDENTIALS`).
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Pod)]
+pub struct UnixCredentials {
+    pid: Pid,
+    uid: Uid,
+    gid: Gid,
+}
+
+impl UnixCredentials {
+    /// Returns the credentials of the current process.
+    pub fn new_current() -> Self {
+        let current = current_thread!();
+        let credentials = current.as_posix_thread().unwrap().credentials();
+
+        Self {
+            pid: current!().pid(),
+            uid: credentials.euid(),
+            gid: credentials.egid(),
+        }
+    }
+
+    /// Returns the credentials that are reported when no credentials are available.
+    ///
+    /// Linux reports a zero PID and invalid user and group IDs in this case.
+    pub const fn new_invalid() -> Self {
+        Self {
+            pid: 0,
+            uid: Uid::new(u32::MAX),
+            gid: Gid::new(u32::MAX),
+        }
+    }
+
+    /// Checks whether the current process is allowed to send the credentials.
+    ///
+    /// A process can only send its own PID and its real, effective, or saved IDs, unless it has
+    /// the corresponding capabilities.
+    ///
+    /// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/net/scm.h#L58>.
+    pub fn check_current(&self) -> Result<()> {
+        let current = current_thread!();
+        let credentials = current.as_posix_thread().unwrap().credentials();
+        let capset = credentials.effective_capset();
+
+        if self.pid != current!().pid() && !capset.contains(CapSet::SYS_ADMIN) {
+            return_errno_with_message!(Errno::EPERM, "the PID cannot be sent");
+        }
+
+        if ![credentials.ruid(), credentials.euid(), credentials.suid()].contains(&self.uid)
+            && !capset.contains(CapSet::SETUID)
+        {
+            return_errno_with_message!(Errno::EPERM, "the user ID cannot be sent");
+        }
+
+        if ![credentials.rgid(), credentials.egid(), credentials.sgid()].contains(&self.gid)
+            && !capset.contains(CapSet::SETGID)
+        {
+            return_errno_with_message!(Errno::EPERM, "the group ID cannot be sent");
+        }
+
+        Ok(())
+    }
+}
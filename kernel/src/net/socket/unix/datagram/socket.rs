@@ -0,0 +1,470 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use ostd::sync::WaitQueue;
+
+use crate::{
+    events::IoEvents,
+    fs::file_handle::FileLike,
+    match_sock_option_mut, match_sock_option_ref,
+    net::socket::{
+        options::{PassCred, PeerCred, SocketOption},
+        private::SocketPrivate,
+        unix::{
+            addr::{UnixSocketAddrBound, UnixSocketAddrKey},
+            ancillary::UnixAuxData,
+            cred::UnixCredentials,
+            gc::{collect_garbage_if_inflight, InflightFiles, InflightQueue},
+            UnixSocketAddr,
+        },
+        util::{send_recv_flags::SendRecvFlags, socket_addr::SocketAddr, MessageHeader},
+        SockShutdownCmd, Socket,
+    },
+    prelude::*,
+    process::signal::{PollHandle, Pollable, Pollee},
+    util::{MultiRead, MultiWrite},
+};
+
+pub struct UnixDatagramSocket {
+    addr: Mutex<Option<UnixSocketAddrBound>>,
+    peer: SpinLock<Option<Weak<UnixDatagramSocket>>>,
+    /// The credentials of the peer, which are only available for a socket pair.
+    peer_cred: Option<UnixCredentials>,
+    messages: Mutex<MessageQueue>,
+    pollee: Pollee,
+    /// The wait queue for the senders that wait for the receive queue to have space.
+    wait_queue: WaitQueue,
+    is_nonblocking: AtomicBool,
+    is_pass_cred: AtomicBool,
+    is_read_shutdown: AtomicBool,
+    is_write_shutdown: AtomicBool,
+    weak_self: Weak<Self>,
+}
+
+#[derive(Default)]
+struct MessageQueue {
+    messages: VecDeque<Message>,
+    total_len: usize,
+}
+
+struct Message {
+    bytes: Vec<u8>,
+    addr: UnixSocketAddr,
+    aux_data: UnixAuxData,
+}
+
+impl Message {
+    /// Returns the size of the message in the receive queue.
+    fn size(&self) -> usize {
+        self.bytes.len() + MESSAGE_OVERHEAD
+    }
+}
+
+impl UnixDatagramSocket {
+    pub fn new(is_nonblocking: bool) -> Arc<Self> {
+        Arc::new_cyclic(|weak_self| Self::new_with_peer(is_nonblocking, None, None, weak_self))
+    }
+
+    pub fn new_pair(is_nonblocking: bool) -> (Arc<Self>, Arc<Self>) {
+        let cred = UnixCredentials::new_current();
+
+        let mut socket_b = None;
+        let socket_a = Arc::new_cyclic(|weak_a| {
+            let b = Arc::new_cyclic(|weak_b| {
+                Self::new_with_peer(is_nonblocking, Some(weak_a.clone()), Some(cred), weak_b)
+            });
+            let a =
+                Self::new_with_peer(is_nonblocking, Some(Arc::downgrade(&b)), Some(cred), weak_a);
+            socket_b = Some(b);
+            a
+        });
+
+        (socket_a, socket_b.unwrap())
+    }
+
+    fn new_with_peer(
+        is_nonblocking: bool,
+        peer: Option<Weak<Self>>,
+        peer_cred: Option<UnixCredentials>,
+        weak_self: &Weak<Self>,
+    ) -> Self {
+        Self {
+            addr: Mutex::new(None),
+            peer: SpinLock::new(peer),
+            peer_cred,
+            messages: Mutex::new(MessageQueue::default()),
+            pollee: Pollee::new(),
+            wait_queue: WaitQueue::new(),
+            is_nonblocking: AtomicBool::new(is_nonblocking),
+            is_pass_cred: AtomicBool::new(false),
+            is_read_shutdown: AtomicBool::new(false),
+            is_write_shutdown: AtomicBool::new(false),
+            weak_self: weak_self.clone(),
+        }
+    }
+
+    fn peer(&self) -> Result<Arc<Self>> {
+        let Some(peer) = self.peer.lock().clone() else {
+            return_errno_with_message!(Errno::ENOTCONN, "the socket is not connected");
+        };
+
+        peer.upgrade().ok_or_else(|| {
+            Error::with_message(Errno::ECONNREFUSED, "the peer socket has been closed")
+        })
+    }
+
+    /// Checks whether `sender` is allowed to send messages to this socket.
+    ///
+    /// A connected socket only receives messages from its peer. This method returns whether
+    /// `sender` is the peer.
+    fn check_sender(&self, sender: &Weak<Self>) -> Result<bool> {
+        let peer = self.peer.lock();
+
+        match peer.as_ref() {
+            Some(peer) if peer.ptr_eq(sender) => Ok(true),
+            Some(peer) if peer.strong_count() > 0 => {
+                return_errno_with_message!(
+                    Errno::EPERM,
+                    "the remote socket is connected to another socket"
+                )
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Tries to push a message to the receive queue.
+    ///
+    /// If the message is pushed, it is taken out of `message`.
+    fn try_push(&self, message: &mut Option<Message>, is_from_peer: bool) -> Result<()> {
+        if self.is_read_shutdown.load(Ordering::Relaxed) {
+            return_errno_with_message!(Errno::EPIPE, "the remote socket is shut down for reading");
+        }
+
+        let mut queue = self.messages.lock();
+
+        // Like Linux, the length of the receive queue is limited by the maximum backlog (i.e.,
+        // `net.unix.max_dgram_qlen`), unless the message comes from the peer. Here we also limit
+        // the total size of the messages, which Linux limits by the send buffer of the sender.
+        let size = message.as_ref().unwrap().size();
+        if (!is_from_peer && queue.messages.len() > MAX_DGRAM_QLEN)
+            || (!queue.messages.is_empty() && queue.total_len + size > RECV_BUF_SIZE)
+        {
+            return_errno_with_message!(Errno::EAGAIN, "the receive queue is full");
+        }
+
+        queue.total_len += size;
+        queue.messages.push_back(message.take().unwrap());
+        drop(queue);
+
+        self.pollee.notify(IoEvents::IN);
+
+        Ok(())
+    }
+
+    fn try_send(&self, target: &Self, message: &mut Option<Message>) -> Result<()> {
+        let is_from_peer = target.check_sender(&self.weak_self)?;
+        target.try_push(message, is_from_peer)
+    }
+
+    fn try_recv(&self) -> Result<Message> {
+        let message = {
+            let mut queue = self.messages.lock();
+            let message = queue.messages.pop_front();
+            if let Some(message) = message.as_ref() {
+                queue.total_len -= message.size();
+            }
+            message
+        };
+        self.pollee.invalidate();
+
+        let Some(message) = message else {
+            if self.is_read_shutdown.load(Ordering::Relaxed) {
+                return Ok(Message {
+                    bytes: Vec::new(),
+                    addr: UnixSocketAddr::Unnamed,
+                    aux_data: UnixAuxData::default(),
+                });
+            }
+            return_errno_with_message!(Errno::EAGAIN, "the receive queue is empty");
+        };
+
+        self.wait_queue.wake_all();
+
+        Ok(message)
+    }
+
+    fn check_io_events(&self) -> IoEvents {
+        let mut events = IoEvents::empty();
+
+        if !self.messages.lock().messages.is_empty() {
+            events |= IoEvents::IN;
+        }
+
+        let is_read_shutdown = self.is_read_shutdown.load(Ordering::Relaxed);
+        let is_write_shutdown = self.is_write_shutdown.load(Ordering::Relaxed);
+
+        if is_read_shutdown {
+            events |= IoEvents::IN | IoEvents::RDHUP;
+        }
+
+        if is_read_shutdown && is_write_shutdown {
+            events |= IoEvents::HUP;
+        }
+
+        // FIXME: The socket should not be writable if the receive queue of the peer is full.
+        if !is_write_shutdown {
+            events |= IoEvents::OUT;
+        }
+
+        events
+    }
+}
+
+impl Pollable for UnixDatagramSocket {
+    fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
+        self.pollee
+            .poll_with(mask, poller, || self.check_io_events())
+    }
+}
+
+impl SocketPrivate for UnixDatagramSocket {
+    fn is_nonblocking(&self) -> bool {
+        self.is_nonblocking.load(Ordering::Relaxed)
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) {
+        self.is_nonblocking.store(nonblocking, Ordering::Relaxed);
+    }
+}
+
+impl Socket for UnixDatagramSocket {
+    fn bind(&self, socket_addr: SocketAddr) -> Result<()> {
+        let addr_to_bind = UnixSocketAddr::try_from(socket_addr)?;
+
+        let mut addr = self.addr.lock();
+
+        if addr.is_some() {
+            return addr_to_bind.bind_unnamed();
+        }
+
+        let bound_addr = addr_to_bind.bind()?;
+        DATAGRAM_TABLE
+            .write()
+            .insert(bound_addr.to_key(), self.weak_self.clone());
+        *addr = Some(bound_addr);
+
+        Ok(())
+    }
+
+    fn connect(&self, socket_addr: SocketAddr) -> Result<()> {
+        let remote_addr = UnixSocketAddr::try_from(socket_addr)?.connect()?;
+        let peer = lookup_socket(&remote_addr)?;
+
+        *self.peer.lock() = Some(Arc::downgrade(&peer));
+
+        Ok(())
+    }
+
+    fn shutdown(&self, cmd: SockShutdownCmd) -> Result<()> {
+        if cmd.shut_read() {
+            self.is_read_shutdown.store(true, Ordering::Relaxed);
+        }
+
+        if cmd.shut_write() {
+            self.is_write_shutdown.store(true, Ordering::Relaxed);
+        }
+
+        self.pollee
+            .notify(IoEvents::IN | IoEvents::OUT | IoEvents::HUP);
+        self.wait_queue.wake_all();
+
+        Ok(())
+    }
+
+    fn addr(&self) -> Result<SocketAddr> {
+        Ok(self.addr.lock().clone().into())
+    }
+
+    fn peer_addr(&self) -> Result<SocketAddr> {
+        let peer = self.peer()?;
+        let peer_addr = peer.addr.lock().clone();
+
+        Ok(peer_addr.into())
+    }
+
+    fn sendmsg(
+        &self,
+        reader: &mut dyn MultiRead,
+        message_header: MessageHeader,
+        flags: SendRecvFlags,
+    ) -> Result<usize> {
+        // TODO: Deal with flags
+        if !flags.is_all_supported() {
+            warn!("unsupported flags: {:?}", flags);
+        }
+
+        let MessageHeader {
+            addr,
+            control_messages,
+        } = message_header;
+
+        if self.is_write_shutdown.load(Ordering::Relaxed) {
+            return_errno_with_message!(Errno::EPIPE, "the socket is shut down for writing");
+        }
+
+        let target = match addr {
+            Some(addr) => lookup_socket(&UnixSocketAddr::try_from(addr)?.connect()?)?,
+            None => self.peer()?,
+        };
+
+        let len = reader.sum_lens();
+        if len > MAX_DGRAM_SIZE {
+            return_errno_with_message!(Errno::EMSGSIZE, "the message is too large");
+        }
+
+        // Note that the ancillary data must be dropped outside `try_send`, since dropping it may
+        // drop the in-flight files (see `InflightFiles`).
+        let mut aux_data = UnixAuxData::from_control_messages(control_messages)?;
+        if self.is_pass_cred.load(Ordering::Relaxed) || target.is_pass_cred.load(Ordering::Relaxed)
+        {
+            aux_data.attach_current_cred();
+        }
+
+        let mut bytes = vec![0u8; len];
+        reader.read(&mut VmWriter::from(bytes.as_mut_slice()))?;
+
+        let mut message = Some(Message {
+            bytes,
+            addr: self.addr.lock().clone().into(),
+            aux_data,
+        });
+
+        if self.is_nonblocking() {
+            self.try_send(&target, &mut message)?;
+        } else {
+            target
+                .wait_queue
+                .pause_until(|| match self.try_send(&target, &mut message) {
+                    Err(err) if err.error() == Errno::EAGAIN => None,
+                    result => Some(result),
+                })??;
+        }
+
+        Ok(len)
+    }
+
+    fn recvmsg(
+        &self,
+        writer: &mut dyn MultiWrite,
+        flags: SendRecvFlags,
+    ) -> Result<(usize, MessageHeader)> {
+        // TODO: Deal with flags
+        if !flags.is_all_supported() {
+            warn!("unsupported flags: {:?}", flags);
+        }
+
+        let Message {
+            bytes,
+            addr,
+            aux_data,
+        } = self.block_on(IoEvents::IN, || self.try_recv())?;
+
+        // TODO: Report the truncation with `MSG_TRUNC`
+        let received_bytes = writer.write(&mut VmReader::from(bytes.as_slice()))?;
+
+        let control_messages =
+            aux_data.into_control_messages(self.is_pass_cred.load(Ordering::Relaxed));
+        let message_header = MessageHeader::new(Some(addr.into()), control_messages);
+
+        Ok((received_bytes, message_header))
+    }
+
+    fn get_option(&self, option: &mut dyn SocketOption) -> Result<()> {
+        match_sock_option_mut!(option, {
+            socket_pass_cred: PassCred => {
+                socket_pass_cred.set(self.is_pass_cred.load(Ordering::Relaxed));
+            },
+            socket_peer_cred: PeerCred => {
+                let peer_cred = self.peer_cred.unwrap_or(UnixCredentials::new_invalid());
+                socket_peer_cred.set(peer_cred);
+            },
+            _ => return_errno_with_message!(Errno::ENOPROTOOPT, "the socket option to get is unknown")
+        });
+
+        Ok(())
+    }
+
+    fn set_option(&self, option: &dyn SocketOption) -> Result<()> {
+        match_sock_option_ref!(option, {
+            socket_pass_cred: PassCred => {
+                let is_pass_cred = socket_pass_cred.get().unwrap();
+                self.is_pass_cred.store(*is_pass_cred, Ordering::Relaxed);
+            },
+            _ => return_errno_with_message!(Errno::ENOPROTOOPT, "the socket option to set is unknown")
+        });
+
+        Ok(())
+    }
+}
+
+impl InflightQueue for UnixDatagramSocket {
+    fn for_each_inflight(&self, f: &mut dyn FnMut(&Arc<dyn FileLike>)) {
+        let queue = self.messages.lock();
+        for message in queue.messages.iter() {
+            message.aux_data.for_each_inflight(f);
+        }
+    }
+
+    fn purge_inflight(&self, purged: &mut Vec<InflightFiles>) {
+        let mut queue = self.messages.lock();
+        for message in queue.messages.iter_mut() {
+            purged.extend(message.aux_data.take_inflight());
+        }
+    }
+}
+
+impl Drop for UnixDatagramSocket {
+    fn drop(&mut self) {
+        if let Some(addr) = self.addr.get_mut().as_ref() {
+            DATAGRAM_TABLE.write().remove(&addr.to_key());
+        }
+
+        // Drop the messages first, so that the in-flight files can become garbage.
+        drop(core::mem::take(self.messages.get_mut()));
+
+        collect_garbage_if_inflight();
+    }
+}
+
+/// The maximum number of messages in the receive queue minus one.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/net/unix/af_unix.c#L3599>.
+const MAX_DGRAM_QLEN: usize = 10;
+
+/// The maximum total size of the messages in the receive queue.
+const RECV_BUF_SIZE: usize = 212992;
+
+/// The maximum size of a message.
+///
+/// Linux limits the size by the send buffer size minus 32 bytes.
+const MAX_DGRAM_SIZE: usize = RECV_BUF_SIZE - 32;
+
+/// The extra size that a message takes in the receive queue.
+const MESSAGE_OVERHEAD: usize = 512;
+
+static DATAGRAM_TABLE: RwLock<BTreeMap<UnixSocketAddrKey, Weak<UnixDatagramSocket>>> =
+    RwLock::new(BTreeMap::new());
+
+fn lookup_socket(addr: &UnixSocketAddrKey) -> Result<Arc<UnixDatagramSocket>> {
+    DATAGRAM_TABLE
+        .read()
+        .get(addr)
+        .and_then(Weak::upgrade)
+        .ok_or_else(|| {
+            Error::with_message(
+                Errno::ECONNREFUSED,
+                "no datagram socket is bound to the remote address",
+            )
+        })
+}
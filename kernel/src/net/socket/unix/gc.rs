@@ -0,0 +1,240 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Garbage collection for the UNIX domain sockets that are in flight.
+//!
+//! A UNIX domain socket can be passed via a UNIX domain socket (including itself) using
+//! `SCM_RIGHTS`. As a result, sockets may form reference cycles through their receive queues,
+//! which keep them alive even after all the file descriptors referring to them are closed. The
+//! garbage collector finds such sockets and purges their receive queues to break the cycles.
+//!
+//! Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/net/unix/garbage.c>.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use super::{UnixDatagramSocket, UnixStreamSocket};
+use crate::{fs::file_handle::FileLike, prelude::*};
+
+/// Files that are in flight, i.e., being passed via a UNIX domain socket.
+///
+/// The UNIX domain sockets among the files are recorded in a global table, so that the garbage
+/// collector can find them.
+///
+/// Note that the global table is locked when an object of this type is created or dropped. So
+/// no other locks that the garbage collector may acquire (e.g., the lock of a receive queue)
+/// should be held at that time.
+pub(super) struct InflightFiles {
+    files: Vec<Arc<dyn FileLike>>,
+}
+
+impl InflightFiles {
+    pub(super) fn new(files: Vec<Arc<dyn FileLike>>) -> Self {
+        let is_gc_needed = {
+            let mut table = INFLIGHT_TABLE.lock();
+            for file in files.iter() {
+                table.add(file);
+            }
+            table.num_sockets > GC_THRESHOLD
+        };
+
+        // Like Linux, we also collect garbage when too many sockets are in flight, so that the
+        // garbage cannot grow without limits.
+        if is_gc_needed {
+            collect_garbage();
+        }
+
+        Self { files }
+    }
+
+    pub(super) fn files(&self) -> &[Arc<dyn FileLike>] {
+        &self.files
+    }
+
+    /// Takes the files out of flight.
+    pub(super) fn into_files(mut self) -> Vec<Arc<dyn FileLike>> {
+        let files = core::mem::take(&mut self.files);
+        INFLIGHT_TABLE.lock().remove(&files);
+        files
+    }
+}
+
+impl Drop for InflightFiles {
+    fn drop(&mut self) {
+        // The files themselves are dropped after the table is unlocked, which is necessary
+        // because dropping a socket may trigger the garbage collector.
+        INFLIGHT_TABLE.lock().remove(&self.files);
+    }
+}
+
+/// A UNIX domain socket whose receive queue may contain in-flight files.
+pub(super) trait InflightQueue {
+    /// Visits the in-flight files in the receive queue.
+    fn for_each_inflight(&self, f: &mut dyn FnMut(&Arc<dyn FileLike>));
+
+    /// Removes all the in-flight files from the receive queue.
+    ///
+    /// The removed files are pushed to `purged` and should be dropped after the global table is
+    /// unlocked.
+    fn purge_inflight(&self, purged: &mut Vec<InflightFiles>);
+}
+
+fn as_inflight_queue(file: &dyn FileLike) -> Option<&dyn InflightQueue> {
+    if let Some(socket) = file.downcast_ref::<UnixStreamSocket>() {
+        return Some(socket);
+    }
+
+    if let Some(socket) = file.downcast_ref::<UnixDatagramSocket>() {
+        return Some(socket);
+    }
+
+    None
+}
+
+/// Collects the garbage if some UNIX domain sockets are in flight.
+///
+/// This method should be called when a UNIX domain socket is released, which is the moment
+/// that the last reference from outside the in-flight files may go away.
+pub(super) fn collect_garbage_if_inflight() {
+    if INFLIGHT_TABLE.lock().num_sockets > 0 {
+        collect_garbage();
+    }
+}
+
+/// Collects the UNIX domain sockets that are only reachable from in-flight files.
+fn collect_garbage() {
+    if IS_GC_IN_PROGRESS.swap(true, Ordering::Acquire) {
+        return;
+    }
+
+    let mut purged = Vec::new();
+    let mut candidates = BTreeMap::new();
+
+    {
+        let table = INFLIGHT_TABLE.lock();
+
+        // Find the candidates whose references all come from in-flight files. Note that one
+        // extra reference is held by `candidates`.
+        for (key, entry) in table.sockets.iter() {
+            let Some(socket) = entry.socket.upgrade() else {
+                continue;
+            };
+            if Arc::strong_count(&socket) == entry.num_inflight + 1 {
+                candidates.insert(*key, (socket, entry.num_inflight));
+            }
+        }
+
+        // Exclude the references that come from the receive queues of the candidates. If some
+        // references remain, the candidate is reachable from outside.
+        let mut num_external_refs: BTreeMap<usize, usize> = candidates
+            .iter()
+            .map(|(key, (_, num_inflight))| (*key, *num_inflight))
+            .collect();
+        for (socket, _) in candidates.values() {
+            as_inflight_queue(socket.as_ref())
+                .unwrap()
+                .for_each_inflight(&mut |file| {
+                    if let Some(num_refs) = num_external_refs.get_mut(&key_of(file)) {
+                        *num_refs = num_refs.saturating_sub(1);
+                    }
+                });
+        }
+
+        // Everything that is reachable from a live candidate is also alive.
+        let mut garbage: BTreeSet<usize> = num_external_refs
+            .iter()
+            .filter(|(_, num_refs)| **num_refs == 0)
+            .map(|(key, _)| *key)
+            .collect();
+        let mut alive: Vec<usize> = num_external_refs
+            .iter()
+            .filter(|(_, num_refs)| **num_refs > 0)
+            .map(|(key, _)| *key)
+            .collect();
+        while let Some(key) = alive.pop() {
+            let (socket, _) = candidates.get(&key).unwrap();
+            as_inflight_queue(socket.as_ref())
+                .unwrap()
+                .for_each_inflight(&mut |file| {
+                    let file_key = key_of(file);
+                    if garbage.remove(&file_key) {
+                        alive.push(file_key);
+                    }
+                });
+        }
+
+        for key in garbage.iter() {
+            let (socket, _) = candidates.get(key).unwrap();
+            as_inflight_queue(socket.as_ref())
+                .unwrap()
+                .purge_inflight(&mut purged);
+        }
+    }
+
+    // Dropping the purged files and the candidates may release the sockets, so it must be done
+    // after the table is unlocked.
+    drop(purged);
+    drop(candidates);
+
+    IS_GC_IN_PROGRESS.store(false, Ordering::Release);
+}
+
+/// The number of in-flight sockets that triggers the garbage collector.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/net/unix/garbage.c#L190>.
+const GC_THRESHOLD: usize = 16000;
+
+static INFLIGHT_TABLE: Mutex<InflightTable> = Mutex::new(InflightTable::new());
+
+static IS_GC_IN_PROGRESS: AtomicBool = AtomicBool::new(false);
+
+struct InflightTable {
+    sockets: BTreeMap<usize, InflightEntry>,
+    num_sockets: usize,
+}
+
+struct InflightEntry {
+    socket: Weak<dyn FileLike>,
+    num_inflight: usize,
+}
+
+impl InflightTable {
+    const fn new() -> Self {
+        Self {
+            sockets: BTreeMap::new(),
+            num_sockets: 0,
+        }
+    }
+
+    fn add(&mut self, file: &Arc<dyn FileLike>) {
+        if as_inflight_queue(file.as_ref()).is_none() {
+            return;
+        }
+
+        self.sockets
+            .entry(key_of(file))
+            .or_insert_with(|| InflightEntry {
+                socket: Arc::downgrade(file),
+                num_inflight: 0,
+            })
+            .num_inflight += 1;
+        self.num_sockets += 1;
+    }
+
+    fn remove(&mut self, files: &[Arc<dyn FileLike>]) {
+        for file in files.iter() {
+            let key = key_of(file);
+            let Some(entry) = self.sockets.get_mut(&key) else {
+                continue;
+            };
+
+            entry.num_inflight -= 1;
+            if entry.num_inflight == 0 {
+                self.sockets.remove(&key);
+            }
+            self.num_sockets -= 1;
+        }
+    }
+}
+
+fn key_of(file: &Arc<dyn FileLike>) -> usize {
+    Arc::as_ptr(file) as *const () as usize
+}
@@ -1,14 +1,20 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use core::ops::Deref;
+use core::sync::atomic::{AtomicBool, Ordering};
 
-use ostd::sync::PreemptDisabled;
+use ostd::mm::Infallible;
 
 use crate::{
     events::IoEvents,
-    fs::utils::{Channel, Consumer, Producer},
+    fs::{
+        file_handle::FileLike,
+        utils::{Channel, Consumer, Producer},
+    },
     net::socket::{
-        unix::{addr::UnixSocketAddrBound, UnixSocketAddr},
+        unix::{
+            addr::UnixSocketAddrBound, ancillary::UnixAuxData, cred::UnixCredentials,
+            gc::InflightFiles, UnixSocketAddr,
+        },
         SockShutdownCmd,
     },
     prelude::*,
@@ -17,15 +23,16 @@ use crate::{
 };
 
 pub(super) struct Connected {
-    addr: AddrView,
+    this: Arc<Endpoint>,
+    peer: Arc<Endpoint>,
     reader: Consumer<u8>,
     writer: Producer<u8>,
 }
 
 impl Connected {
     pub(super) fn new_pair(
-        addr: Option<UnixSocketAddrBound>,
-        peer_addr: Option<UnixSocketAddrBound>,
+        this: Arc<Endpoint>,
+        peer: Arc<Endpoint>,
         reader_pollee: Option<Pollee>,
         writer_pollee: Option<Pollee>,
     ) -> (Connected, Connected) {
@@ -34,32 +41,40 @@ impl Connected {
         let (writer_this, reader_peer) =
             Channel::with_capacity_and_pollees(DEFAULT_BUF_SIZE, writer_pollee, None).split();
 
-        let (addr_this, addr_peer) = AddrView::new_pair(addr, peer_addr);
-
-        let this = Connected {
-            addr: addr_this,
+        let this_conn = Connected {
+            this: this.clone(),
+            peer: peer.clone(),
             reader: reader_this,
             writer: writer_this,
         };
-        let peer = Connected {
-            addr: addr_peer,
+        let peer_conn = Connected {
+            this: peer,
+            peer: this,
             reader: reader_peer,
             writer: writer_peer,
         };
 
-        (this, peer)
+        (this_conn, peer_conn)
     }
 
     pub(super) fn addr(&self) -> Option<UnixSocketAddrBound> {
-        self.addr.addr().deref().as_ref().cloned()
+        self.this.addr.lock().as_ref().cloned()
     }
 
     pub(super) fn peer_addr(&self) -> Option<UnixSocketAddrBound> {
-        self.addr.peer_addr()
+        self.peer.addr.lock().as_ref().cloned()
+    }
+
+    pub(super) fn peer_cred(&self) -> UnixCredentials {
+        self.peer.cred
+    }
+
+    pub(super) fn is_pass_cred(&self) -> &Arc<AtomicBool> {
+        &self.this.is_pass_cred
     }
 
     pub(super) fn bind(&self, addr_to_bind: UnixSocketAddr) -> Result<()> {
-        let mut addr = self.addr.addr();
+        let mut addr = self.this.addr.lock();
 
         if addr.is_some() {
             return addr_to_bind.bind_unnamed();
@@ -71,12 +86,50 @@ impl Connected {
         Ok(())
     }
 
-    pub(super) fn try_read(&self, writer: &mut dyn MultiWrite) -> Result<usize> {
-        self.reader.try_read(writer)
+    /// Reads the data and the ancillary data attached to it.
+    ///
+    /// Like Linux, the data attached to different ancillary data will never be read at once.
+    pub(super) fn try_read(&self, writer: &mut dyn MultiWrite) -> Result<(usize, UnixAuxData)> {
+        let mut aux_queue = self.this.aux_queue.lock();
+
+        let aux_data = aux_queue.pop_front();
+        let read_res = match aux_queue.next_len() {
+            Some(limit) => self.reader.try_read(&mut LimitedWriter { writer, limit }),
+            None => self.reader.try_read(writer),
+        };
+
+        match read_res {
+            Ok(read_len) if read_len > 0 => {
+                aux_queue.read_pos += read_len;
+                Ok((read_len, aux_data.unwrap_or_default()))
+            }
+            res => {
+                aux_queue.push_front(aux_data);
+                res.map(|read_len| (read_len, UnixAuxData::default()))
+            }
+        }
     }
 
-    pub(super) fn try_write(&self, reader: &mut dyn MultiRead) -> Result<usize> {
-        self.writer.try_write(reader)
+    /// Writes the data and attaches the ancillary data to it.
+    ///
+    /// If some data is written, the ancillary data is taken out of `aux_data`.
+    pub(super) fn try_write(
+        &self,
+        reader: &mut dyn MultiRead,
+        aux_data: &mut UnixAuxData,
+    ) -> Result<usize> {
+        if self.this.is_pass_cred.load(Ordering::Relaxed)
+            || self.peer.is_pass_cred.load(Ordering::Relaxed)
+        {
+            aux_data.attach_current_cred();
+        }
+
+        let mut aux_queue = self.peer.aux_queue.lock();
+
+        let written_len = self.writer.try_write(reader)?;
+        aux_queue.push_back(written_len, aux_data);
+
+        Ok(written_len)
     }
 
     pub(super) fn shutdown(&self, cmd: SockShutdownCmd) {
@@ -96,6 +149,20 @@ impl Connected {
 
         combine_io_events(mask, reader_events, writer_events)
     }
+
+    pub(super) fn for_each_inflight(&self, f: &mut dyn FnMut(&Arc<dyn FileLike>)) {
+        let aux_queue = self.this.aux_queue.lock();
+        for (_, aux_data) in aux_queue.entries.iter() {
+            aux_data.for_each_inflight(f);
+        }
+    }
+
+    pub(super) fn purge_inflight(&self, purged: &mut Vec<InflightFiles>) {
+        let mut aux_queue = self.this.aux_queue.lock();
+        for (_, aux_data) in aux_queue.entries.iter_mut() {
+            purged.extend(aux_data.take_inflight());
+        }
+    }
 }
 
 pub(super) fn combine_io_events(
@@ -122,37 +189,101 @@ pub(super) fn combine_io_events(
     events & (mask | IoEvents::ALWAYS_POLL)
 }
 
-struct AddrView {
-    addr: Arc<SpinLock<Option<UnixSocketAddrBound>>>,
-    peer: Arc<SpinLock<Option<UnixSocketAddrBound>>>,
+/// One end of a connection.
+pub(super) struct Endpoint {
+    addr: SpinLock<Option<UnixSocketAddrBound>>,
+    /// The credentials of the process that created the end.
+    cred: UnixCredentials,
+    is_pass_cred: Arc<AtomicBool>,
+    /// The ancillary data attached to the data received by the end.
+    aux_queue: Mutex<AuxQueue>,
 }
 
-impl AddrView {
-    fn new_pair(
-        first: Option<UnixSocketAddrBound>,
-        second: Option<UnixSocketAddrBound>,
-    ) -> (AddrView, AddrView) {
-        let first = Arc::new(SpinLock::new(first));
-        let second = Arc::new(SpinLock::new(second));
-
-        let view1 = AddrView {
-            addr: first.clone(),
-            peer: second.clone(),
-        };
-        let view2 = AddrView {
-            addr: second,
-            peer: first,
-        };
+impl Endpoint {
+    pub(super) fn new(
+        addr: Option<UnixSocketAddrBound>,
+        cred: UnixCredentials,
+        is_pass_cred: Arc<AtomicBool>,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            addr: SpinLock::new(addr),
+            cred,
+            is_pass_cred,
+            aux_queue: Mutex::new(AuxQueue::default()),
+        })
+    }
+}
+
+/// A queue that records the ancillary data attached to the data in a channel.
+///
+/// The data is located by the number of bytes ever transferred via the channel.
+#[derive(Default)]
+struct AuxQueue {
+    /// The ancillary data and the start positions of the data it is attached to.
+    ///
+    /// Empty ancillary data marks the end of the data that the previous ancillary data is
+    /// attached to.
+    entries: VecDeque<(usize, UnixAuxData)>,
+    write_pos: usize,
+    read_pos: usize,
+    is_end_pending: bool,
+}
 
-        (view1, view2)
+impl AuxQueue {
+    fn push_back(&mut self, len: usize, aux_data: &mut UnixAuxData) {
+        if len == 0 {
+            return;
+        }
+
+        if !aux_data.is_empty() {
+            self.entries
+                .push_back((self.write_pos, core::mem::take(aux_data)));
+            self.is_end_pending = true;
+        } else if self.is_end_pending {
+            self.entries
+                .push_back((self.write_pos, UnixAuxData::default()));
+            self.is_end_pending = false;
+        }
+
+        self.write_pos += len;
     }
 
-    fn addr(&self) -> SpinLockGuard<Option<UnixSocketAddrBound>, PreemptDisabled> {
-        self.addr.lock()
+    /// Pops the ancillary data attached to the next data to read.
+    fn pop_front(&mut self) -> Option<UnixAuxData> {
+        match self.entries.front() {
+            Some((pos, _)) if *pos == self.read_pos => Some(self.entries.pop_front().unwrap().1),
+            _ => None,
+        }
+    }
+
+    /// Pushes back the ancillary data popped by [`Self::pop_front`] if no data is read.
+    fn push_front(&mut self, aux_data: Option<UnixAuxData>) {
+        if let Some(aux_data) = aux_data {
+            self.entries.push_front((self.read_pos, aux_data));
+        }
+    }
+
+    /// Returns the length of the data before the next ancillary data starts.
+    fn next_len(&self) -> Option<usize> {
+        self.entries.front().map(|(pos, _)| *pos - self.read_pos)
+    }
+}
+
+/// A writer that writes at most `limit` bytes.
+struct LimitedWriter<'a> {
+    writer: &'a mut dyn MultiWrite,
+    limit: usize,
+}
+
+impl MultiWrite for LimitedWriter<'_> {
+    fn write(&mut self, reader: &mut VmReader<'_, Infallible>) -> Result<usize> {
+        let written_len = self.writer.write(reader.limit(self.limit))?;
+        self.limit -= written_len;
+        Ok(written_len)
     }
 
-    fn peer_addr(&self) -> Option<UnixSocketAddrBound> {
-        self.peer.lock().as_ref().cloned()
+    fn sum_lens(&self) -> usize {
+        self.writer.sum_lens().min(self.limit)
     }
 }
 
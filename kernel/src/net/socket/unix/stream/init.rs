@@ -3,13 +3,16 @@
 use core::sync::atomic::{AtomicBool, Ordering};
 
 use super::{
-    connected::{combine_io_events, Connected},
+    connected::{combine_io_events, Connected, Endpoint},
     listener::Listener,
 };
 use crate::{
     events::IoEvents,
     net::socket::{
-        unix::addr::{UnixSocketAddr, UnixSocketAddrBound},
+        unix::{
+            addr::{UnixSocketAddr, UnixSocketAddrBound},
+            cred::UnixCredentials,
+        },
         SockShutdownCmd,
     },
     prelude::*,
@@ -22,16 +25,18 @@ pub(super) struct Init {
     writer_pollee: Pollee,
     is_read_shutdown: AtomicBool,
     is_write_shutdown: AtomicBool,
+    is_pass_cred: Arc<AtomicBool>,
 }
 
 impl Init {
-    pub(super) fn new() -> Self {
+    pub(super) fn new(is_pass_cred: Arc<AtomicBool>) -> Self {
         Self {
             addr: None,
             reader_pollee: Pollee::new(),
             writer_pollee: Pollee::new(),
             is_read_shutdown: AtomicBool::new(false),
             is_write_shutdown: AtomicBool::new(false),
+            is_pass_cred,
         }
     }
 
@@ -46,21 +51,19 @@ impl Init {
         Ok(())
     }
 
-    pub(super) fn into_connected(self, peer_addr: UnixSocketAddrBound) -> (Connected, Connected) {
+    pub(super) fn into_connected(self, peer: Arc<Endpoint>) -> (Connected, Connected) {
         let Init {
             addr,
             reader_pollee,
             writer_pollee,
             is_read_shutdown,
             is_write_shutdown,
+            is_pass_cred,
         } = self;
 
-        let (this_conn, peer_conn) = Connected::new_pair(
-            addr,
-            Some(peer_addr),
-            Some(reader_pollee),
-            Some(writer_pollee),
-        );
+        let this = Endpoint::new(addr, UnixCredentials::new_current(), is_pass_cred);
+        let (this_conn, peer_conn) =
+            Connected::new_pair(this, peer, Some(reader_pollee), Some(writer_pollee));
 
         if is_read_shutdown.into_inner() {
             this_conn.shutdown(SockShutdownCmd::SHUT_RD);
@@ -88,6 +91,7 @@ impl Init {
             backlog,
             self.is_read_shutdown.into_inner(),
             self.is_write_shutdown.into_inner(),
+            self.is_pass_cred,
         ))
     }
 
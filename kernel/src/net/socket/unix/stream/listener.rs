@@ -5,7 +5,7 @@ use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use ostd::sync::WaitQueue;
 
 use super::{
-    connected::{combine_io_events, Connected},
+    connected::{combine_io_events, Connected, Endpoint},
     init::Init,
     UnixStreamSocket,
 };
@@ -13,7 +13,11 @@ use crate::{
     events::IoEvents,
     fs::file_handle::FileLike,
     net::socket::{
-        unix::addr::{UnixSocketAddrBound, UnixSocketAddrKey},
+        unix::{
+            addr::{UnixSocketAddrBound, UnixSocketAddrKey},
+            cred::UnixCredentials,
+            gc::InflightFiles,
+        },
         SockShutdownCmd, SocketAddr,
     },
     prelude::*,
@@ -34,9 +38,10 @@ impl Listener {
         backlog: usize,
         is_read_shutdown: bool,
         is_write_shutdown: bool,
+        is_pass_cred: Arc<AtomicBool>,
     ) -> Self {
         let backlog = BACKLOG_TABLE
-            .add_backlog(addr, reader_pollee, backlog, is_read_shutdown)
+            .add_backlog(addr, reader_pollee, backlog, is_read_shutdown, is_pass_cred)
             .unwrap();
         writer_pollee.invalidate();
 
@@ -51,6 +56,10 @@ impl Listener {
         self.backlog.addr()
     }
 
+    pub(super) fn cred(&self) -> UnixCredentials {
+        self.backlog.cred
+    }
+
     pub(super) fn try_accept(&self) -> Result<(Arc<dyn FileLike>, SocketAddr)> {
         let connected = self.backlog.pop_incoming()?;
         let peer_addr = connected.peer_addr().into();
@@ -93,6 +102,20 @@ impl Listener {
 
         combine_io_events(mask, reader_events, writer_events)
     }
+
+    pub(super) fn for_each_inflight(&self, f: &mut dyn FnMut(&Arc<dyn FileLike>)) {
+        let incoming_conns = self.backlog.incoming_conns.lock();
+        for conn in incoming_conns.iter().flatten() {
+            conn.for_each_inflight(f);
+        }
+    }
+
+    pub(super) fn purge_inflight(&self, purged: &mut Vec<InflightFiles>) {
+        let incoming_conns = self.backlog.incoming_conns.lock();
+        for conn in incoming_conns.iter().flatten() {
+            conn.purge_inflight(purged);
+        }
+    }
 }
 
 impl Drop for Listener {
@@ -122,6 +145,7 @@ impl BacklogTable {
         pollee: Pollee,
         backlog: usize,
         is_shutdown: bool,
+        is_pass_cred: Arc<AtomicBool>,
     ) -> Option<Arc<Backlog>> {
         let addr_key = addr.to_key();
 
@@ -133,7 +157,13 @@ impl BacklogTable {
 
         // Note that the cached events can be correctly inherited from `Init`, so there is no need
         // to explicitly call `Pollee::invalidate`.
-        let new_backlog = Arc::new(Backlog::new(addr, pollee, backlog, is_shutdown));
+        let new_backlog = Arc::new(Backlog::new(
+            addr,
+            pollee,
+            backlog,
+            is_shutdown,
+            is_pass_cred,
+        ));
         backlog_sockets.insert(addr_key, new_backlog.clone());
 
         Some(new_backlog)
@@ -150,14 +180,23 @@ impl BacklogTable {
 
 pub(super) struct Backlog {
     addr: UnixSocketAddrBound,
+    /// The credentials of the process that started listening.
+    cred: UnixCredentials,
+    is_pass_cred: Arc<AtomicBool>,
     pollee: Pollee,
     backlog: AtomicUsize,
-    incoming_conns: SpinLock<Option<VecDeque<Connected>>>,
+    incoming_conns: Mutex<Option<VecDeque<Connected>>>,
     wait_queue: WaitQueue,
 }
 
 impl Backlog {
-    fn new(addr: UnixSocketAddrBound, pollee: Pollee, backlog: usize, is_shutdown: bool) -> Self {
+    fn new(
+        addr: UnixSocketAddrBound,
+        pollee: Pollee,
+        backlog: usize,
+        is_shutdown: bool,
+        is_pass_cred: Arc<AtomicBool>,
+    ) -> Self {
         let incoming_sockets = if is_shutdown {
             None
         } else {
@@ -166,9 +205,11 @@ impl Backlog {
 
         Self {
             addr,
+            cred: UnixCredentials::new_current(),
+            is_pass_cred,
             pollee,
             backlog: AtomicUsize::new(backlog),
-            incoming_conns: SpinLock::new(incoming_sockets),
+            incoming_conns: Mutex::new(incoming_sockets),
             wait_queue: WaitQueue::new(),
         }
     }
@@ -205,10 +246,13 @@ impl Backlog {
     fn shutdown(&self) {
         let mut incoming_conns = self.incoming_conns.lock();
 
-        *incoming_conns = None;
+        // The connections are dropped after the lock is released, since dropping them may drop
+        // the in-flight files (see `InflightFiles`).
+        let dropped_conns = incoming_conns.take();
         self.pollee.notify(IoEvents::HUP);
 
         drop(incoming_conns);
+        drop(dropped_conns);
 
         self.wait_queue.wake_all();
     }
@@ -260,7 +304,13 @@ impl Backlog {
             ));
         }
 
-        let (client_conn, server_conn) = init.into_connected(self.addr.clone());
+        // Like Linux, the accepted socket inherits `SO_PASSCRED` from the listening socket.
+        let server = Endpoint::new(
+            Some(self.addr.clone()),
+            self.cred,
+            Arc::new(AtomicBool::new(self.is_pass_cred.load(Ordering::Relaxed))),
+        );
+        let (client_conn, server_conn) = init.into_connected(server);
 
         incoming_conns.push_back(server_conn);
         self.pollee.notify(IoEvents::IN);
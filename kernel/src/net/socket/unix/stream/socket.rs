@@ -5,16 +5,23 @@ use core::sync::atomic::{AtomicBool, Ordering};
 use takeable::Takeable;
 
 use super::{
-    connected::Connected,
+    connected::{Connected, Endpoint},
     init::Init,
     listener::{get_backlog, Backlog, Listener},
 };
 use crate::{
     events::IoEvents,
     fs::file_handle::FileLike,
+    match_sock_option_mut, match_sock_option_ref,
     net::socket::{
+        options::{PassCred, PeerCred, SocketOption},
         private::SocketPrivate,
-        unix::UnixSocketAddr,
+        unix::{
+            ancillary::UnixAuxData,
+            cred::UnixCredentials,
+            gc::{collect_garbage_if_inflight, InflightFiles, InflightQueue},
+            UnixSocketAddr,
+        },
         util::{send_recv_flags::SendRecvFlags, socket_addr::SocketAddr, MessageHeader},
         SockShutdownCmd, Socket,
     },
@@ -26,20 +33,17 @@ use crate::{
 pub struct UnixStreamSocket {
     state: RwMutex<Takeable<State>>,
     is_nonblocking: AtomicBool,
+    is_pass_cred: Arc<AtomicBool>,
 }
 
 impl UnixStreamSocket {
-    pub(super) fn new_init(init: Init, is_nonblocking: bool) -> Arc<Self> {
-        Arc::new(Self {
-            state: RwMutex::new(Takeable::new(State::Init(init))),
-            is_nonblocking: AtomicBool::new(is_nonblocking),
-        })
-    }
-
     pub(super) fn new_connected(connected: Connected, is_nonblocking: bool) -> Arc<Self> {
+        let is_pass_cred = connected.is_pass_cred().clone();
+
         Arc::new(Self {
             state: RwMutex::new(Takeable::new(State::Connected(connected))),
             is_nonblocking: AtomicBool::new(is_nonblocking),
+            is_pass_cred,
         })
     }
 }
@@ -52,27 +56,46 @@ enum State {
 
 impl UnixStreamSocket {
     pub fn new(is_nonblocking: bool) -> Arc<Self> {
-        Self::new_init(Init::new(), is_nonblocking)
+        let is_pass_cred = Arc::new(AtomicBool::new(false));
+
+        Arc::new(Self {
+            state: RwMutex::new(Takeable::new(State::Init(Init::new(is_pass_cred.clone())))),
+            is_nonblocking: AtomicBool::new(is_nonblocking),
+            is_pass_cred,
+        })
     }
 
     pub fn new_pair(is_nonblocking: bool) -> (Arc<Self>, Arc<Self>) {
-        let (conn_a, conn_b) = Connected::new_pair(None, None, None, None);
+        let cred = UnixCredentials::new_current();
+        let endpoint_a = Endpoint::new(None, cred, Arc::new(AtomicBool::new(false)));
+        let endpoint_b = Endpoint::new(None, cred, Arc::new(AtomicBool::new(false)));
+
+        let (conn_a, conn_b) = Connected::new_pair(endpoint_a, endpoint_b, None, None);
         (
             Self::new_connected(conn_a, is_nonblocking),
             Self::new_connected(conn_b, is_nonblocking),
         )
     }
 
-    fn try_send(&self, buf: &mut dyn MultiRead, _flags: SendRecvFlags) -> Result<usize> {
+    fn try_send(
+        &self,
+        buf: &mut dyn MultiRead,
+        aux_data: &mut UnixAuxData,
+        _flags: SendRecvFlags,
+    ) -> Result<usize> {
         match self.state.read().as_ref() {
-            State::Connected(connected) => connected.try_write(buf),
+            State::Connected(connected) => connected.try_write(buf, aux_data),
             State::Init(_) | State::Listen(_) => {
                 return_errno_with_message!(Errno::ENOTCONN, "the socket is not connected")
             }
         }
     }
 
-    fn try_recv(&self, buf: &mut dyn MultiWrite, _flags: SendRecvFlags) -> Result<usize> {
+    fn try_recv(
+        &self,
+        buf: &mut dyn MultiWrite,
+        _flags: SendRecvFlags,
+    ) -> Result<(usize, UnixAuxData)> {
         match self.state.read().as_ref() {
             State::Connected(connected) => connected.try_read(buf),
             State::Init(_) | State::Listen(_) => {
@@ -258,15 +281,16 @@ impl Socket for UnixStreamSocket {
         }
 
         let MessageHeader {
-            control_message, ..
+            control_messages, ..
         } = message_header;
 
-        if control_message.is_some() {
-            // TODO: Support sending control message
-            warn!("sending control message is not supported");
-        }
+        // Note that the ancillary data must be dropped outside `try_send`, since dropping it may
+        // drop the in-flight files (see `InflightFiles`).
+        let mut aux_data = UnixAuxData::from_control_messages(control_messages)?;
 
-        self.block_on(IoEvents::OUT, || self.try_send(reader, flags))
+        self.block_on(IoEvents::OUT, || {
+            self.try_send(reader, &mut aux_data, flags)
+        })
     }
 
     fn recvmsg(
@@ -279,12 +303,72 @@ impl Socket for UnixStreamSocket {
             warn!("unsupported flags: {:?}", flags);
         }
 
-        let received_bytes = self.block_on(IoEvents::IN, || self.try_recv(writer, flags))?;
-
-        // TODO: Receive control message
+        let (received_bytes, aux_data) =
+            self.block_on(IoEvents::IN, || self.try_recv(writer, flags))?;
 
-        let message_header = MessageHeader::new(None, None);
+        let control_messages =
+            aux_data.into_control_messages(self.is_pass_cred.load(Ordering::Relaxed));
+        let message_header = MessageHeader::new(None, control_messages);
 
         Ok((received_bytes, message_header))
     }
+
+    fn get_option(&self, option: &mut dyn SocketOption) -> Result<()> {
+        match_sock_option_mut!(option, {
+            socket_pass_cred: PassCred => {
+                socket_pass_cred.set(self.is_pass_cred.load(Ordering::Relaxed));
+            },
+            socket_peer_cred: PeerCred => {
+                let peer_cred = match self.state.read().as_ref() {
+                    State::Connected(connected) => connected.peer_cred(),
+                    // Like Linux, a listening socket reports its own credentials.
+                    State::Listen(listener) => listener.cred(),
+                    State::Init(_) => UnixCredentials::new_invalid(),
+                };
+                socket_peer_cred.set(peer_cred);
+            },
+            _ => return_errno_with_message!(Errno::ENOPROTOOPT, "the socket option to get is unknown")
+        });
+
+        Ok(())
+    }
+
+    fn set_option(&self, option: &dyn SocketOption) -> Result<()> {
+        match_sock_option_ref!(option, {
+            socket_pass_cred: PassCred => {
+                let is_pass_cred = socket_pass_cred.get().unwrap();
+                self.is_pass_cred.store(*is_pass_cred, Ordering::Relaxed);
+            },
+            _ => return_errno_with_message!(Errno::ENOPROTOOPT, "the socket option to set is unknown")
+        });
+
+        Ok(())
+    }
+}
+
+impl InflightQueue for UnixStreamSocket {
+    fn for_each_inflight(&self, f: &mut dyn FnMut(&Arc<dyn FileLike>)) {
+        match self.state.read().as_ref() {
+            State::Init(_) => (),
+            State::Listen(listener) => listener.for_each_inflight(f),
+            State::Connected(connected) => connected.for_each_inflight(f),
+        }
+    }
+
+    fn purge_inflight(&self, purged: &mut Vec<InflightFiles>) {
+        match self.state.read().as_ref() {
+            State::Init(_) => (),
+            State::Listen(listener) => listener.purge_inflight(purged),
+            State::Connected(connected) => connected.purge_inflight(purged),
+        }
+    }
+}
+
+impl Drop for UnixStreamSocket {
+    fn drop(&mut self) {
+        // Drop the state first, so that the in-flight files can become garbage.
+        drop(self.state.get_mut().take());
+
+        collect_garbage_if_inflight();
+    }
 }
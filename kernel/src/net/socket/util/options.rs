@@ -1,17 +1,24 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use core::time::Duration;
+use core::{
+    sync::atomic::{AtomicU32, Ordering},
+    time::Duration,
+};
 
 use aster_bigtcp::socket::{
-    NeedIfacePoll, TCP_RECV_BUF_LEN, TCP_SEND_BUF_LEN, UDP_RECV_PAYLOAD_LEN, UDP_SEND_PAYLOAD_LEN,
+    NeedIfacePoll, LINK_RECV_BUF_LEN, LINK_SEND_BUF_LEN, TCP_RECV_BUF_LEN, TCP_SEND_BUF_LEN,
+    UDP_RECV_PAYLOAD_LEN, UDP_SEND_PAYLOAD_LEN,
 };
 
+use super::message_header::ControlMessage;
 use crate::{
     match_sock_option_mut, match_sock_option_ref,
     net::socket::options::{
-        KeepAlive, Linger, RecvBuf, ReuseAddr, ReusePort, SendBuf, SocketOption,
+        KeepAlive, Linger, RecvBuf, ReuseAddr, ReusePort, SendBuf, SocketOption, Timestamp,
+        TimestampNs,
     },
     prelude::*,
+    time::clocks::RealTimeClock,
 };
 
 #[derive(Debug, Clone, CopyGetters, Setters)]
@@ -24,6 +31,7 @@ pub struct SocketOptionSet {
     recv_buf: u32,
     linger: LingerOption,
     keep_alive: bool,
+    timestamp: Option<TimestampFormat>,
 }
 
 impl SocketOptionSet {
@@ -36,6 +44,7 @@ impl SocketOptionSet {
             recv_buf: TCP_RECV_BUF_LEN as u32,
             linger: LingerOption::default(),
             keep_alive: false,
+            timestamp: None,
         }
     }
 
@@ -48,6 +57,33 @@ impl SocketOptionSet {
             recv_buf: UDP_RECV_PAYLOAD_LEN as u32,
             linger: LingerOption::default(),
             keep_alive: false,
+            timestamp: None,
+        }
+    }
+
+    /// Return the default socket level options for netlink socket.
+    pub fn new_netlink() -> Self {
+        Self {
+            reuse_addr: false,
+            reuse_port: false,
+            send_buf: DEFAULT_SENDBUF.load(Ordering::Relaxed),
+            recv_buf: DEFAULT_RECVBUF.load(Ordering::Relaxed),
+            linger: LingerOption::default(),
+            keep_alive: false,
+            timestamp: None,
+        }
+    }
+
+    /// Return the default socket level options for packet socket.
+    pub fn new_packet() -> Self {
+        Self {
+            reuse_addr: false,
+            reuse_port: false,
+            send_buf: LINK_SEND_BUF_LEN as u32,
+            recv_buf: LINK_RECV_BUF_LEN as u32,
+            linger: LingerOption::default(),
+            keep_alive: false,
+            timestamp: None,
         }
     }
 
@@ -82,6 +118,14 @@ impl SocketOptionSet {
                 let keep_alive = self.keep_alive();
                 socket_keepalive.set(keep_alive);
             },
+            socket_timestamp: Timestamp => {
+                let timestamp = self.timestamp();
+                socket_timestamp.set(timestamp == Some(TimestampFormat::Micros));
+            },
+            socket_timestamp_ns: TimestampNs => {
+                let timestamp = self.timestamp();
+                socket_timestamp_ns.set(timestamp == Some(TimestampFormat::Nanos));
+            },
             _ => return_errno_with_message!(Errno::ENOPROTOOPT, "the socket option to get is unknown")
         });
         Ok(())
@@ -95,20 +139,16 @@ impl SocketOptionSet {
     ) -> Result<NeedIfacePoll> {
         match_sock_option_ref!(option, {
             socket_recv_buf: RecvBuf => {
-                let recv_buf = socket_recv_buf.get().unwrap();
-                if *recv_buf <= MIN_RECVBUF {
-                    self.set_recv_buf(MIN_RECVBUF);
-                } else {
-                    self.set_recv_buf(*recv_buf);
-                }
+                // Like Linux, the value is doubled to leave space for bookkeeping overhead.
+                let max_recv_buf = MAX_RECVBUF.load(Ordering::Relaxed);
+                let recv_buf = (*socket_recv_buf.get().unwrap()).min(max_recv_buf) * 2;
+                self.set_recv_buf(recv_buf.max(MIN_RECVBUF));
             },
             socket_send_buf: SendBuf => {
-                let send_buf = socket_send_buf.get().unwrap();
-                if *send_buf <= MIN_SENDBUF {
-                    self.set_send_buf(MIN_SENDBUF);
-                } else {
-                    self.set_send_buf(*send_buf);
-                }
+                // Like Linux, the value is doubled to leave space for bookkeeping overhead.
+                let max_send_buf = MAX_SENDBUF.load(Ordering::Relaxed);
+                let send_buf = (*socket_send_buf.get().unwrap()).min(max_send_buf) * 2;
+                self.set_send_buf(send_buf.max(MIN_SENDBUF));
             },
             socket_reuse_addr: ReuseAddr => {
                 let reuse_addr = socket_reuse_addr.get().unwrap();
@@ -127,16 +167,59 @@ impl SocketOptionSet {
                 self.set_keep_alive(*keep_alive);
                 return Ok(socket.set_keep_alive(*keep_alive));
             },
+            socket_timestamp: Timestamp => {
+                let timestamp = socket_timestamp.get().unwrap();
+                self.set_timestamp(timestamp.then_some(TimestampFormat::Micros));
+            },
+            socket_timestamp_ns: TimestampNs => {
+                let timestamp_ns = socket_timestamp_ns.get().unwrap();
+                self.set_timestamp(timestamp_ns.then_some(TimestampFormat::Nanos));
+            },
             _ => return_errno_with_message!(Errno::ENOPROTOOPT, "the socket option to be set is unknown")
         });
 
         Ok(NeedIfacePoll::FALSE)
     }
+
+    /// Returns the control message that carries the receive timestamp.
+    ///
+    /// If receive timestamps are not enabled via `SO_TIMESTAMP` or `SO_TIMESTAMPNS`, this method
+    /// will return `None`.
+    //
+    // FIXME: The timestamp should be taken when the message arrives at the network interface,
+    // not when the message is received by the user.
+    pub fn timestamp_message(&self) -> Option<ControlMessage> {
+        let now = || RealTimeClock::get().read_time();
+        match self.timestamp? {
+            TimestampFormat::Micros => Some(ControlMessage::Timestamp(now())),
+            TimestampFormat::Nanos => Some(ControlMessage::TimestampNs(now())),
+        }
+    }
 }
 
 pub const MIN_SENDBUF: u32 = 2304;
 pub const MIN_RECVBUF: u32 = 2304;
 
+/// The maximum sizes that can be set by `SO_SNDBUF` and `SO_RCVBUF`, which are
+/// `/proc/sys/net/core/wmem_max` and `/proc/sys/net/core/rmem_max`.
+pub static MAX_SENDBUF: AtomicU32 = AtomicU32::new(212992);
+pub static MAX_RECVBUF: AtomicU32 = AtomicU32::new(212992);
+
+/// The default sizes of the buffers of the sockets without protocol-specific defaults
+/// (e.g., netlink sockets), which are `/proc/sys/net/core/wmem_default` and
+/// `/proc/sys/net/core/rmem_default`.
+pub static DEFAULT_SENDBUF: AtomicU32 = AtomicU32::new(212992);
+pub static DEFAULT_RECVBUF: AtomicU32 = AtomicU32::new(212992);
+
+/// The format of receive timestamps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampFormat {
+    /// The timestamps are reported in `struct timeval` (`SO_TIMESTAMP`).
+    Micros,
+    /// The timestamps are reported in `struct timespec` (`SO_TIMESTAMPNS`).
+    Nanos,
+}
+
 #[derive(Debug, Default, Clone, Copy)]
 pub struct LingerOption {
     is_on: bool,
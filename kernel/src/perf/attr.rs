@@ -0,0 +1,138 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use crate::prelude::*;
+
+/// The attributes of a perf event, i.e., `struct perf_event_attr`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub struct PerfEventAttr {
+    pub type_: u32,
+    pub size: u32,
+    pub config: u64,
+    /// The sampling period, or the sampling frequency if `PERF_ATTR_FLAG_FREQ` is set.
+    pub sample_period: u64,
+    pub sample_type: u64,
+    pub read_format: u64,
+    pub flags: u64,
+    pub wakeup_events: u32,
+    pub bp_type: u32,
+    pub config1: u64,
+    pub config2: u64,
+    pub branch_sample_type: u64,
+    pub sample_regs_user: u64,
+    pub sample_stack_user: u32,
+    pub clockid: i32,
+    pub sample_regs_intr: u64,
+    pub aux_watermark: u32,
+    pub sample_max_stack: u16,
+    pub _reserved_2: u16,
+    pub aux_sample_size: u32,
+    pub _reserved_3: u32,
+    pub sig_data: u64,
+    pub config3: u64,
+}
+
+/// The size of the attributes of the first version, which is assumed if the size is zero.
+pub const PERF_ATTR_SIZE_VER0: usize = 64;
+
+pub const PERF_TYPE_HARDWARE: u32 = 0;
+pub const PERF_TYPE_SOFTWARE: u32 = 1;
+
+pub const PERF_COUNT_SW_TASK_CLOCK: u64 = 1;
+pub const PERF_COUNT_SW_PAGE_FAULTS: u64 = 2;
+pub const PERF_COUNT_SW_CONTEXT_SWITCHES: u64 = 3;
+pub const PERF_COUNT_SW_DUMMY: u64 = 9;
+
+bitflags! {
+    /// The flags in the bit fields of the attributes.
+    pub struct PerfAttrFlags: u64 {
+        const DISABLED = 1 << 0;
+        const INHERIT = 1 << 1;
+        const PINNED = 1 << 2;
+        const EXCLUSIVE = 1 << 3;
+        const EXCLUDE_USER = 1 << 4;
+        const EXCLUDE_KERNEL = 1 << 5;
+        const EXCLUDE_HV = 1 << 6;
+        const EXCLUDE_IDLE = 1 << 7;
+        const MMAP = 1 << 8;
+        const COMM = 1 << 9;
+        const FREQ = 1 << 10;
+        const INHERIT_STAT = 1 << 11;
+        const ENABLE_ON_EXEC = 1 << 12;
+        const TASK = 1 << 13;
+        const WATERMARK = 1 << 14;
+        const PRECISE_IP = 0b11 << 15;
+        const MMAP_DATA = 1 << 17;
+        const SAMPLE_ID_ALL = 1 << 18;
+        const EXCLUDE_HOST = 1 << 19;
+        const EXCLUDE_GUEST = 1 << 20;
+        const EXCLUDE_CALLCHAIN_KERNEL = 1 << 21;
+        const EXCLUDE_CALLCHAIN_USER = 1 << 22;
+        const MMAP2 = 1 << 23;
+        const COMM_EXEC = 1 << 24;
+        const USE_CLOCKID = 1 << 25;
+        const CONTEXT_SWITCH = 1 << 26;
+        const WRITE_BACKWARD = 1 << 27;
+        const NAMESPACES = 1 << 28;
+        const KSYMBOL = 1 << 29;
+        const BPF_EVENT = 1 << 30;
+        const AUX_OUTPUT = 1 << 31;
+        const CGROUP = 1 << 32;
+        const TEXT_POKE = 1 << 33;
+        const BUILD_ID = 1 << 34;
+        const INHERIT_THREAD = 1 << 35;
+        const REMOVE_ON_EXEC = 1 << 36;
+        const SIGTRAP = 1 << 37;
+    }
+}
+
+impl PerfAttrFlags {
+    /// The flags that request the records other than the samples, which are accepted but
+    /// never generated.
+    pub const SIDEBAND: Self = Self::MMAP
+        .union(Self::COMM)
+        .union(Self::TASK)
+        .union(Self::MMAP_DATA)
+        .union(Self::SAMPLE_ID_ALL)
+        .union(Self::MMAP2)
+        .union(Self::COMM_EXEC)
+        .union(Self::CONTEXT_SWITCH)
+        .union(Self::NAMESPACES)
+        .union(Self::KSYMBOL)
+        .union(Self::BPF_EVENT)
+        .union(Self::CGROUP)
+        .union(Self::TEXT_POKE)
+        .union(Self::BUILD_ID);
+
+    /// The flags that are not supported, with which the events cannot be opened.
+    pub const UNSUPPORTED: Self = Self::WRITE_BACKWARD
+        .union(Self::AUX_OUTPUT)
+        .union(Self::INHERIT_THREAD)
+        .union(Self::REMOVE_ON_EXEC)
+        .union(Self::SIGTRAP);
+}
+
+bitflags! {
+    /// The fields in the samples.
+    pub struct PerfSampleType: u64 {
+        const IP = 1 << 0;
+        const TID = 1 << 1;
+        const TIME = 1 << 2;
+        const ADDR = 1 << 3;
+        const CALLCHAIN = 1 << 5;
+        const ID = 1 << 6;
+        const CPU = 1 << 7;
+        const PERIOD = 1 << 8;
+        const STREAM_ID = 1 << 9;
+        const IDENTIFIER = 1 << 16;
+    }
+}
+
+bitflags! {
+    /// The fields in the values read from the events.
+    pub struct PerfReadFormat: u64 {
+        const TOTAL_TIME_ENABLED = 1 << 0;
+        const TOTAL_TIME_RUNNING = 1 << 1;
+        const ID = 1 << 2;
+    }
+}
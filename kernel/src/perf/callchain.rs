@@ -0,0 +1,72 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use ostd::{cpu::context::UserContext, user::UserContextApi};
+
+use crate::prelude::*;
+
+/// The maximum number of the entries in a callchain, i.e., `PERF_MAX_STACK_DEPTH`.
+pub(super) const MAX_STACK_DEPTH: usize = 127;
+
+/// The marker that precedes the user-space entries in a callchain.
+pub(super) const PERF_CONTEXT_USER: u64 = -512i64 as u64;
+
+/// Walks the user stack by the frame pointers, and returns the instruction pointer followed by
+/// the return addresses.
+///
+/// The walk stops at the first frame that cannot be read or looks invalid, so the callchain
+/// is truncated if the user program is compiled without the frame pointers.
+pub(super) fn user_callchain(ctx: &Context, user_ctx: &UserContext) -> Vec<u64> {
+    let mut ips = Vec::new();
+    ips.push(user_ctx.instruction_pointer() as u64);
+
+    let user_space = ctx.user_space();
+    let mut fp = frame_pointer(user_ctx);
+    while ips.len() < MAX_STACK_DEPTH {
+        if fp == 0 || fp % align_of::<usize>() != 0 {
+            break;
+        }
+        let Ok((ret_addr, prev_fp)) = read_frame(&user_space, fp) else {
+            break;
+        };
+        if ret_addr == 0 {
+            break;
+        }
+        ips.push(ret_addr as u64);
+
+        // The stack grows downwards, so the previous frames must be at higher addresses.
+        if prev_fp <= fp {
+            break;
+        }
+        fp = prev_fp;
+    }
+
+    ips
+}
+
+/// Returns the frame pointer of the user context.
+fn frame_pointer(user_ctx: &UserContext) -> Vaddr {
+    #[cfg(target_arch = "riscv64")]
+    return user_ctx.s0();
+    #[cfg(target_arch = "x86_64")]
+    return user_ctx.rbp();
+}
+
+/// Reads the return address and the previous frame pointer of the frame.
+fn read_frame(user_space: &CurrentUserSpace, fp: Vaddr) -> Result<(Vaddr, Vaddr)> {
+    // On RISC-V, the return address and the previous frame pointer are saved below the frame
+    // pointer, which points to the top of the frame.
+    #[cfg(target_arch = "riscv64")]
+    let (ret_addr, prev_fp) = (
+        user_space.read_val::<usize>(fp.checked_sub(8).ok_or(Errno::EFAULT)?)?,
+        user_space.read_val::<usize>(fp.checked_sub(16).ok_or(Errno::EFAULT)?)?,
+    );
+    // On x86-64, the previous frame pointer is saved where the frame pointer points to, and is
+    // followed by the return address.
+    #[cfg(target_arch = "x86_64")]
+    let (ret_addr, prev_fp) = (
+        user_space.read_val::<usize>(fp.checked_add(8).ok_or(Errno::EFAULT)?)?,
+        user_space.read_val::<usize>(fp)?,
+    );
+
+    Ok((ret_addr, prev_fp))
+}
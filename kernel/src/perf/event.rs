@@ -0,0 +1,591 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+#[cfg(target_arch = "riscv64")]
+use ostd::arch::pmu::{self, ExcludeModes, HwEvent};
+use ostd::{
+    cpu::{context::UserContext, CpuId},
+    task::Task,
+    user::UserContextApi,
+};
+
+use super::{
+    attr::{
+        PerfAttrFlags, PerfEventAttr, PerfReadFormat, PerfSampleType,
+        PERF_COUNT_SW_CONTEXT_SWITCHES, PERF_COUNT_SW_DUMMY, PERF_COUNT_SW_PAGE_FAULTS,
+        PERF_COUNT_SW_TASK_CLOCK, PERF_TYPE_HARDWARE, PERF_TYPE_SOFTWARE,
+    },
+    callchain::{self, PERF_CONTEXT_USER},
+    ring::{RecordBuilder, RingBuffer, PERF_RECORD_MISC_USER, PERF_RECORD_SAMPLE},
+};
+use crate::{
+    current_userspace,
+    events::IoEvents,
+    fs::{
+        file_handle::FileLike,
+        utils::{InodeMode, InodeType, IoctlCmd, Metadata},
+    },
+    prelude::*,
+    process::{
+        posix_thread::AsPosixThread,
+        signal::{PollHandle, Pollable, Pollee},
+        Gid, Uid,
+    },
+    thread::{Thread, Tid},
+    time::clocks::{MonotonicClock, RealTimeClock},
+    vm::vmo::Vmo,
+};
+
+/// A perf event, which counts the occurrences of an event in a thread.
+///
+/// The event is counted only when it is enabled (see [`PerfEvent::enable`]), and only when the
+/// thread runs on the CPU specified in [`PerfEvent::new`] if any. If the event has a
+/// ring buffer and a sampling period or frequency, a sample is written to the ring buffer when
+/// the thread enters the kernel after enough events have occurred.
+pub struct PerfEvent {
+    id: u64,
+    kind: PerfEventKind,
+    tid: Tid,
+    thread: Weak<Thread>,
+    cpu: Option<CpuId>,
+    sample_type: PerfSampleType,
+    read_format: PerfReadFormat,
+    state: SpinLock<EventState>,
+    ring: Mutex<Option<RingBuffer>>,
+    pollee: Pollee,
+    weak_self: Weak<PerfEvent>,
+}
+
+/// The kind of a perf event.
+#[derive(Debug, Clone, Copy)]
+pub(super) enum PerfEventKind {
+    /// A hardware event, which is counted by a PMU counter.
+    #[cfg(target_arch = "riscv64")]
+    Hardware(HwEvent, ExcludeModes),
+    /// A software event, which is counted by the kernel.
+    Software(SwEvent),
+}
+
+/// The software events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum SwEvent {
+    /// The CPU time of the thread in nanoseconds.
+    TaskClock,
+    /// The page faults of the thread.
+    PageFaults,
+    /// The times that the thread is switched to.
+    ContextSwitches,
+    /// An event that never occurs.
+    Dummy,
+}
+
+/// The way to take samples.
+#[derive(Debug, Clone, Copy)]
+enum Sampling {
+    /// A sample is taken every the number of events.
+    Period(u64),
+    /// A sample is taken at about the number of times per second.
+    Freq(u64),
+}
+
+#[derive(Debug)]
+struct EventState {
+    is_enabled: bool,
+    enable_on_exec: bool,
+    /// The number of the events that are counted, except those counted by a running PMU
+    /// counter (see [`super::ActiveCounter`]).
+    count: u64,
+    /// The CPU time of the thread when the event is enabled, for [`SwEvent::TaskClock`].
+    task_clock_base: Duration,
+    /// Whether the events counted by the running PMU counter should be discarded, because the
+    /// event is reset or disabled in another thread while the counter is running.
+    discard_running: bool,
+    /// The total time that the event is enabled, except the time since `enabled_at`.
+    time_enabled: Duration,
+    enabled_at: Duration,
+    sampling: Option<Sampling>,
+    last_sample_count: u64,
+    last_sample_time: Duration,
+}
+
+impl PerfEventKind {
+    fn from_attr(attr: &PerfEventAttr) -> Result<Self> {
+        match attr.type_ {
+            PERF_TYPE_HARDWARE => Self::hardware_from_attr(attr),
+            PERF_TYPE_SOFTWARE => {
+                let sw_event = match attr.config {
+                    PERF_COUNT_SW_TASK_CLOCK => SwEvent::TaskClock,
+                    PERF_COUNT_SW_PAGE_FAULTS => SwEvent::PageFaults,
+                    PERF_COUNT_SW_CONTEXT_SWITCHES => SwEvent::ContextSwitches,
+                    PERF_COUNT_SW_DUMMY => SwEvent::Dummy,
+                    _ => return_errno_with_message!(Errno::ENOENT, "the software event is unknown"),
+                };
+                Ok(Self::Software(sw_event))
+            }
+            _ => return_errno_with_message!(Errno::ENOENT, "the event type is not supported"),
+        }
+    }
+
+    #[cfg(target_arch = "riscv64")]
+    fn hardware_from_attr(attr: &PerfEventAttr) -> Result<Self> {
+        if !pmu::is_available() {
+            return_errno_with_message!(Errno::ENOENT, "the PMU is not available");
+        }
+
+        let hw_event = match attr.config {
+            0 => HwEvent::CpuCycles,
+            1 => HwEvent::Instructions,
+            2 => HwEvent::CacheReferences,
+            3 => HwEvent::CacheMisses,
+            4 => HwEvent::BranchInstructions,
+            5 => HwEvent::BranchMisses,
+            6 => HwEvent::BusCycles,
+            7 => HwEvent::StalledCyclesFrontend,
+            8 => HwEvent::StalledCyclesBackend,
+            9 => HwEvent::RefCpuCycles,
+            _ => return_errno_with_message!(Errno::ENOENT, "the hardware event is unknown"),
+        };
+        let flags = PerfAttrFlags::from_bits_truncate(attr.flags);
+        let exclude = ExcludeModes {
+            user: flags.contains(PerfAttrFlags::EXCLUDE_USER),
+            kernel: flags.contains(PerfAttrFlags::EXCLUDE_KERNEL),
+            firmware: flags.contains(PerfAttrFlags::EXCLUDE_HV),
+        };
+        Ok(Self::Hardware(hw_event, exclude))
+    }
+
+    #[cfg(not(target_arch = "riscv64"))]
+    fn hardware_from_attr(_attr: &PerfEventAttr) -> Result<Self> {
+        return_errno_with_message!(Errno::ENOENT, "the PMU is not supported")
+    }
+}
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+impl PerfEvent {
+    /// Creates an event that is counted in the thread, and only on the CPU if it is specified.
+    ///
+    /// The event is enabled unless `PERF_ATTR_FLAG_DISABLED` is set in the attributes.
+    pub fn new(attr: &PerfEventAttr, thread: Arc<Thread>, cpu: Option<CpuId>) -> Result<Arc<Self>> {
+        let kind = PerfEventKind::from_attr(attr)?;
+        let flags = PerfAttrFlags::from_bits_truncate(attr.flags);
+        let sampling = if attr.sample_period == 0 {
+            None
+        } else if flags.contains(PerfAttrFlags::FREQ) {
+            Some(Sampling::Freq(attr.sample_period))
+        } else {
+            Some(Sampling::Period(attr.sample_period))
+        };
+        let tid = thread.as_posix_thread().unwrap().tid();
+
+        let event = Arc::new_cyclic(|weak_self| Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            kind,
+            tid,
+            thread: Arc::downgrade(&thread),
+            cpu,
+            sample_type: PerfSampleType::from_bits_truncate(attr.sample_type),
+            read_format: PerfReadFormat::from_bits_truncate(attr.read_format),
+            state: SpinLock::new(EventState {
+                is_enabled: false,
+                enable_on_exec: flags.contains(PerfAttrFlags::ENABLE_ON_EXEC),
+                count: 0,
+                task_clock_base: Duration::ZERO,
+                discard_running: false,
+                time_enabled: Duration::ZERO,
+                enabled_at: Duration::ZERO,
+                sampling,
+                last_sample_count: 0,
+                last_sample_time: Duration::ZERO,
+            }),
+            ring: Mutex::new(None),
+            pollee: Pollee::new(),
+            weak_self: weak_self.clone(),
+        });
+        super::register_event(&event);
+
+        if !flags.contains(PerfAttrFlags::DISABLED) {
+            event.enable();
+        }
+        Ok(event)
+    }
+
+    /// Returns the TID of the thread in which the event is counted.
+    pub(super) fn tid(&self) -> Tid {
+        self.tid
+    }
+
+    pub(super) fn kind(&self) -> PerfEventKind {
+        self.kind
+    }
+
+    /// Returns whether the event is counted on the CPU.
+    pub(super) fn is_counted_on(&self, cpu: CpuId) -> bool {
+        self.cpu.is_none_or(|event_cpu| event_cpu == cpu)
+    }
+
+    /// Enables the event.
+    pub fn enable(&self) {
+        {
+            let mut state = self.state.lock();
+            if state.is_enabled {
+                return;
+            }
+            state.is_enabled = true;
+            state.enable_on_exec = false;
+            state.enabled_at = MonotonicClock::get().read_time();
+            state.last_sample_time = state.enabled_at;
+            if let Some(task_clock) = self.task_clock() {
+                state.task_clock_base = task_clock;
+            }
+        }
+
+        // The PMU counter of the thread on another CPU is started when the thread is switched
+        // to next time.
+        if self.is_current() {
+            if let Some(this) = self.weak_self.upgrade() {
+                super::start_counter(&this);
+            }
+        }
+    }
+
+    /// Disables the event, and keeps the number of the events that are counted.
+    pub fn disable(&self) {
+        let running = if self.is_current() {
+            super::stop_counter(self)
+        } else {
+            None
+        };
+
+        let mut state = self.state.lock();
+        if !state.is_enabled {
+            return;
+        }
+        state.is_enabled = false;
+        if let Some(task_clock) = self.task_clock() {
+            state.count += (task_clock - state.task_clock_base).as_nanos() as u64;
+        }
+        match running {
+            Some(count) => state.count += count,
+            None => state.discard_running = true,
+        }
+        let now = MonotonicClock::get().read_time();
+        state.time_enabled += now - state.enabled_at;
+    }
+
+    /// Resets the number of the events to zero.
+    pub fn reset(&self) {
+        let is_running = self.is_current() && super::reset_counter(self);
+
+        let mut state = self.state.lock();
+        state.count = 0;
+        state.last_sample_count = 0;
+        if let Some(task_clock) = self.task_clock() {
+            state.task_clock_base = task_clock;
+        }
+        if !is_running {
+            state.discard_running = true;
+        }
+    }
+
+    /// Enables the event if it should be enabled when the thread calls `execve`.
+    pub(super) fn enable_on_exec(&self) {
+        if !core::mem::take(&mut self.state.lock().enable_on_exec) {
+            return;
+        }
+        self.enable();
+    }
+
+    /// Returns the number of the events that are counted.
+    ///
+    /// The events counted by a PMU counter are included only if the counter is running on the
+    /// current CPU or has been stopped.
+    pub fn count(&self) -> u64 {
+        let running = if self.is_current() {
+            super::read_counter(self).unwrap_or(0)
+        } else {
+            0
+        };
+
+        let state = self.state.lock();
+        let mut count = state.count + running;
+        if state.is_enabled {
+            if let Some(task_clock) = self.task_clock() {
+                count += (task_clock - state.task_clock_base).as_nanos() as u64;
+            }
+        }
+        count
+    }
+
+    /// Returns whether a PMU counter should be started for the event, which is called before
+    /// the counter is started.
+    pub(super) fn begin_running(&self) -> bool {
+        let mut state = self.state.lock();
+        state.discard_running = false;
+        state.is_enabled
+    }
+
+    /// Adds the number of the events that are counted by a PMU counter, which has been
+    /// stopped.
+    pub(super) fn add_running_count(&self, count: u64) {
+        let mut state = self.state.lock();
+        if core::mem::take(&mut state.discard_running) || !state.is_enabled {
+            return;
+        }
+        state.count += count;
+    }
+
+    /// Counts an occurrence of the software event.
+    pub(super) fn add_one(&self) {
+        let mut state = self.state.lock();
+        if state.is_enabled {
+            state.count += 1;
+        }
+    }
+
+    fn time_enabled(&self) -> Duration {
+        let state = self.state.lock();
+        if state.is_enabled {
+            state.time_enabled + (MonotonicClock::get().read_time() - state.enabled_at)
+        } else {
+            state.time_enabled
+        }
+    }
+
+    /// Returns the CPU time of the thread if the event is [`SwEvent::TaskClock`].
+    fn task_clock(&self) -> Option<Duration> {
+        if !matches!(self.kind, PerfEventKind::Software(SwEvent::TaskClock)) {
+            return None;
+        }
+        let thread = self.thread.upgrade()?;
+        Some(thread.as_posix_thread()?.prof_clock().read_time())
+    }
+
+    fn is_current(&self) -> bool {
+        Task::current().and_then(|task| {
+            task.as_posix_thread()
+                .map(|posix_thread| posix_thread.tid())
+        }) == Some(self.tid)
+    }
+
+    /// Sets the sampling period, or the sampling frequency if the event is sampled by the
+    /// frequency.
+    fn set_period(&self, period: u64) -> Result<()> {
+        if period == 0 {
+            return_errno_with_message!(Errno::EINVAL, "the sampling period cannot be zero");
+        }
+
+        let mut state = self.state.lock();
+        state.sampling = match state.sampling {
+            Some(Sampling::Freq(_)) => Some(Sampling::Freq(period)),
+            Some(Sampling::Period(_)) => Some(Sampling::Period(period)),
+            None => {
+                return_errno_with_message!(Errno::EINVAL, "the event is not a sampling event")
+            }
+        };
+        Ok(())
+    }
+
+    /// Takes a sample if enough events have occurred since the last sample.
+    ///
+    /// This is called when the thread enters the kernel from the user space.
+    pub(super) fn sample(&self, ctx: &Context, user_ctx: &UserContext, cpu: CpuId) {
+        let count = self.count();
+        let now = MonotonicClock::get().read_time();
+
+        let period = {
+            let mut state = self.state.lock();
+            if !state.is_enabled {
+                return;
+            }
+            let is_due = match state.sampling {
+                None => return,
+                Some(Sampling::Period(period)) => {
+                    count.saturating_sub(state.last_sample_count) >= period
+                }
+                Some(Sampling::Freq(freq)) => {
+                    (now - state.last_sample_time).as_nanos() as u64 >= 1_000_000_000 / freq
+                }
+            };
+            if !is_due {
+                return;
+            }
+            let period = count.saturating_sub(state.last_sample_count);
+            state.last_sample_count = count;
+            state.last_sample_time = now;
+            period
+        };
+
+        let mut ring = self.ring.lock();
+        let Some(ring) = ring.as_mut() else {
+            return;
+        };
+
+        let mut record = RecordBuilder::new(PERF_RECORD_SAMPLE, PERF_RECORD_MISC_USER);
+        if self.sample_type.contains(PerfSampleType::IDENTIFIER) {
+            record.push_u64(self.id);
+        }
+        if self.sample_type.contains(PerfSampleType::IP) {
+            record.push_u64(user_ctx.instruction_pointer() as u64);
+        }
+        if self.sample_type.contains(PerfSampleType::TID) {
+            record.push_u32_pair(ctx.process.pid(), self.tid);
+        }
+        if self.sample_type.contains(PerfSampleType::TIME) {
+            record.push_u64(now.as_nanos() as u64);
+        }
+        if self.sample_type.contains(PerfSampleType::ADDR) {
+            record.push_u64(0);
+        }
+        if self.sample_type.contains(PerfSampleType::ID) {
+            record.push_u64(self.id);
+        }
+        if self.sample_type.contains(PerfSampleType::STREAM_ID) {
+            record.push_u64(self.id);
+        }
+        if self.sample_type.contains(PerfSampleType::CPU) {
+            record.push_u32_pair(cpu.as_usize() as u32, 0);
+        }
+        if self.sample_type.contains(PerfSampleType::PERIOD) {
+            record.push_u64(period);
+        }
+        if self.sample_type.contains(PerfSampleType::CALLCHAIN) {
+            let ips = callchain::user_callchain(ctx, user_ctx);
+            record.push_u64(ips.len() as u64 + 1);
+            record.push_u64(PERF_CONTEXT_USER);
+            for ip in ips {
+                record.push_u64(ip);
+            }
+        }
+
+        ring.push(&record.finish(), self.id);
+        self.pollee.notify(IoEvents::IN);
+    }
+
+    /// Returns the VMO of the ring buffer, which is created if it does not exist.
+    pub fn mmap_vmo(&self, len: usize) -> Result<Vmo> {
+        let mut ring = self.ring.lock();
+        if let Some(ring) = ring.as_ref() {
+            if ring.size() != len {
+                return_errno_with_message!(Errno::EINVAL, "the ring buffer has another size");
+            }
+            return ring.vmo().dup();
+        }
+
+        let new_ring = RingBuffer::new(len)?;
+        let vmo = new_ring.vmo().dup()?;
+        *ring = Some(new_ring);
+        Ok(vmo)
+    }
+
+    fn check_io_events(&self) -> IoEvents {
+        match self.ring.lock().as_ref() {
+            Some(ring) if ring.has_records() => IoEvents::IN,
+            _ => IoEvents::empty(),
+        }
+    }
+}
+
+impl Drop for PerfEvent {
+    fn drop(&mut self) {
+        super::unregister_event();
+    }
+}
+
+impl Debug for PerfEvent {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("PerfEvent")
+            .field("id", &self.id)
+            .field("kind", &self.kind)
+            .field("tid", &self.tid)
+            .field("cpu", &self.cpu)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Pollable for PerfEvent {
+    fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
+        // The user space consumes the records in the ring buffer without any system calls, so
+        // the cached events may be stale.
+        self.pollee.invalidate();
+
+        self.pollee
+            .poll_with(mask, poller, || self.check_io_events())
+    }
+}
+
+impl FileLike for PerfEvent {
+    fn read(&self, writer: &mut VmWriter) -> Result<usize> {
+        let mut values = vec![self.count()];
+        if self
+            .read_format
+            .contains(PerfReadFormat::TOTAL_TIME_ENABLED)
+        {
+            values.push(self.time_enabled().as_nanos() as u64);
+        }
+        // The events are never multiplexed, so they are running whenever they are enabled.
+        if self
+            .read_format
+            .contains(PerfReadFormat::TOTAL_TIME_RUNNING)
+        {
+            values.push(self.time_enabled().as_nanos() as u64);
+        }
+        if self.read_format.contains(PerfReadFormat::ID) {
+            values.push(self.id);
+        }
+
+        let len = values.len() * size_of::<u64>();
+        if writer.avail() < len {
+            return_errno_with_message!(Errno::ENOSPC, "the buffer is too small");
+        }
+        for value in values {
+            writer.write_val(&value)?;
+        }
+        Ok(len)
+    }
+
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        match cmd {
+            // The events are not grouped, so `PERF_IOC_FLAG_GROUP` in `arg` makes no difference.
+            IoctlCmd::PERF_EVENT_IOC_ENABLE => self.enable(),
+            IoctlCmd::PERF_EVENT_IOC_DISABLE => self.disable(),
+            IoctlCmd::PERF_EVENT_IOC_RESET => self.reset(),
+            IoctlCmd::PERF_EVENT_IOC_PERIOD => {
+                let period: u64 = current_userspace!().read_val(arg)?;
+                self.set_period(period)?;
+            }
+            IoctlCmd::PERF_EVENT_IOC_ID => {
+                current_userspace!().write_val(arg, &self.id)?;
+            }
+            _ => return_errno_with_message!(Errno::ENOTTY, "the ioctl command is unknown"),
+        }
+        Ok(0)
+    }
+
+    fn metadata(&self) -> Metadata {
+        // This is a dummy implementation.
+        // TODO: Add "anonymous inode fs" and link `PerfEvent` to it.
+        let now = RealTimeClock::get().read_time();
+        Metadata {
+            dev: 0,
+            ino: 0,
+            size: 0,
+            blk_size: 0,
+            blocks: 0,
+            atime: now,
+            mtime: now,
+            ctime: now,
+            btime: None,
+            type_: InodeType::File,
+            mode: InodeMode::from_bits_truncate(0o600),
+            nlinks: 1,
+            uid: Uid::new_root(),
+            gid: Gid::new_root(),
+            rdev: 0,
+        }
+    }
+}
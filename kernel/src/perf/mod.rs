@@ -0,0 +1,272 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Performance monitoring, i.e., the perf events.
+//!
+//! A perf event is opened by the `perf_event_open` system call and counts an event in a thread.
+//! The hardware events (e.g., the CPU cycles) are counted by the PMU counters (see
+//! [`ostd::arch::pmu`]), which are started when the thread is switched to and stopped when it
+//! is switched out. The software events (e.g., the page faults) are counted by the kernel at
+//! the hooks in this module.
+//!
+//! An event can also take samples into a ring buffer that is mapped to the user space, which
+//! is how `perf record` works. Unlike Linux, the PMU overflow interrupts are not supported, so
+//! the samples are taken when the thread enters the kernel (e.g., for a system call or a page
+//! fault) after enough events have occurred, and the period in each sample is the number of the
+//! events since the last sample.
+//!
+//! Compared to Linux, the events cannot be grouped, inherited by the children or counted for
+//! all the threads on a CPU, and no records other than the samples are generated.
+//!
+//! Reference: <https://man7.org/linux/man-pages/man2/perf_event_open.2.html>
+
+pub mod attr;
+mod callchain;
+mod event;
+mod ring;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+pub use event::PerfEvent;
+use event::{PerfEventKind, SwEvent};
+use ostd::cpu::context::UserContext;
+#[cfg(target_arch = "riscv64")]
+use ostd::{arch::pmu::PmuCounter, cpu::PinCurrentCpu, task::disable_preempt};
+
+use crate::{
+    prelude::*,
+    process::posix_thread::AsPosixThread,
+    thread::{Thread, Tid},
+};
+
+/// The events that are opened, along with the TIDs of the threads in which they are counted.
+static EVENTS: SpinLock<Vec<(Tid, Weak<PerfEvent>)>> = SpinLock::new(Vec::new());
+
+/// The number of the events that are opened, with which the hooks return quickly if no events
+/// are opened.
+static NR_EVENTS: AtomicUsize = AtomicUsize::new(0);
+
+fn register_event(event: &Arc<PerfEvent>) {
+    EVENTS.lock().push((event.tid(), Arc::downgrade(event)));
+    NR_EVENTS.fetch_add(1, Ordering::Relaxed);
+}
+
+fn unregister_event() {
+    EVENTS.lock().retain(|(_, event)| event.strong_count() != 0);
+    NR_EVENTS.fetch_sub(1, Ordering::Relaxed);
+}
+
+/// Returns the events that are counted in the thread.
+fn events_of(tid: Tid) -> Vec<Arc<PerfEvent>> {
+    if NR_EVENTS.load(Ordering::Relaxed) == 0 {
+        return Vec::new();
+    }
+
+    // The events are dropped after the lock is released, since dropping an event locks it.
+    EVENTS
+        .lock()
+        .iter()
+        .filter(|(event_tid, _)| *event_tid == tid)
+        .filter_map(|(_, event)| event.upgrade())
+        .collect()
+}
+
+/// A PMU counter that is counting an event for the thread running on the CPU.
+#[cfg(target_arch = "riscv64")]
+struct ActiveCounter {
+    event: Arc<PerfEvent>,
+    counter: PmuCounter,
+    /// The value of the counter when the event is last reset.
+    base: u64,
+}
+
+#[cfg(target_arch = "riscv64")]
+ostd::cpu_local! {
+    /// The PMU counters that are counting on the CPU.
+    static ACTIVE_COUNTERS: SpinLock<Vec<ActiveCounter>> = SpinLock::new(Vec::new());
+}
+
+/// Starts a PMU counter on the current CPU if the event is a hardware event.
+///
+/// The event must be counted in the current thread.
+fn start_counter(event: &Arc<PerfEvent>) {
+    #[cfg(target_arch = "riscv64")]
+    {
+        let PerfEventKind::Hardware(hw_event, exclude) = event.kind() else {
+            return;
+        };
+
+        let preempt_guard = disable_preempt();
+        let cpu = preempt_guard.current_cpu();
+        if !event.is_counted_on(cpu) {
+            return;
+        }
+
+        let mut active_counters = ACTIVE_COUNTERS.get_on_cpu(cpu).lock();
+        if active_counters
+            .iter()
+            .any(|active| Arc::ptr_eq(&active.event, event))
+            || !event.begin_running()
+        {
+            return;
+        }
+        match PmuCounter::start(hw_event, exclude, &preempt_guard) {
+            Ok(counter) => active_counters.push(ActiveCounter {
+                event: event.clone(),
+                counter,
+                base: 0,
+            }),
+            Err(err) => warn!(
+                "failed to start a PMU counter for {:?}: {:?}",
+                hw_event, err
+            ),
+        }
+    }
+    #[cfg(not(target_arch = "riscv64"))]
+    let _ = event;
+}
+
+/// Stops the PMU counter of the event on the current CPU, and returns the number of the events
+/// that it has counted.
+///
+/// It returns `None` if the counter is not running on the current CPU.
+fn stop_counter(event: &PerfEvent) -> Option<u64> {
+    #[cfg(target_arch = "riscv64")]
+    {
+        let preempt_guard = disable_preempt();
+        let mut active_counters = ACTIVE_COUNTERS
+            .get_on_cpu(preempt_guard.current_cpu())
+            .lock();
+        let index = active_counters
+            .iter()
+            .position(|active| core::ptr::eq(Arc::as_ptr(&active.event), event))?;
+        let active = active_counters.swap_remove(index);
+        drop(active_counters);
+
+        let value = active.counter.stop(&preempt_guard);
+        Some(value.wrapping_sub(active.base))
+    }
+    #[cfg(not(target_arch = "riscv64"))]
+    {
+        let _ = event;
+        None
+    }
+}
+
+/// Reads the PMU counter of the event on the current CPU.
+///
+/// It returns `None` if the counter is not running on the current CPU.
+fn read_counter(event: &PerfEvent) -> Option<u64> {
+    #[cfg(target_arch = "riscv64")]
+    {
+        let preempt_guard = disable_preempt();
+        let active_counters = ACTIVE_COUNTERS
+            .get_on_cpu(preempt_guard.current_cpu())
+            .lock();
+        let active = active_counters
+            .iter()
+            .find(|active| core::ptr::eq(Arc::as_ptr(&active.event), event))?;
+        Some(
+            active
+                .counter
+                .read(&preempt_guard)
+                .wrapping_sub(active.base),
+        )
+    }
+    #[cfg(not(target_arch = "riscv64"))]
+    {
+        let _ = event;
+        None
+    }
+}
+
+/// Resets the PMU counter of the event on the current CPU.
+///
+/// It returns `false` if the counter is not running on the current CPU.
+fn reset_counter(event: &PerfEvent) -> bool {
+    #[cfg(target_arch = "riscv64")]
+    {
+        let preempt_guard = disable_preempt();
+        let mut active_counters = ACTIVE_COUNTERS
+            .get_on_cpu(preempt_guard.current_cpu())
+            .lock();
+        let Some(active) = active_counters
+            .iter_mut()
+            .find(|active| core::ptr::eq(Arc::as_ptr(&active.event), event))
+        else {
+            return false;
+        };
+        active.base = active.counter.read(&preempt_guard);
+        true
+    }
+    #[cfg(not(target_arch = "riscv64"))]
+    {
+        let _ = event;
+        false
+    }
+}
+
+/// Switches the events when a context switch happens on the current CPU.
+///
+/// The PMU counters of the previous thread are stopped, and those of the next thread are
+/// started.
+pub(crate) fn on_sched_switch(next_thread: Option<&Arc<Thread>>) {
+    #[cfg(target_arch = "riscv64")]
+    {
+        let preempt_guard = disable_preempt();
+        let stopped = core::mem::take(
+            &mut *ACTIVE_COUNTERS
+                .get_on_cpu(preempt_guard.current_cpu())
+                .lock(),
+        );
+        for active in stopped {
+            let value = active.counter.stop(&preempt_guard);
+            active
+                .event
+                .add_running_count(value.wrapping_sub(active.base));
+        }
+    }
+
+    let Some(posix_thread) = next_thread.and_then(|thread| thread.as_posix_thread()) else {
+        return;
+    };
+    let cpu = ostd::cpu::current_cpu_racy();
+    for event in events_of(posix_thread.tid()) {
+        match event.kind() {
+            PerfEventKind::Software(SwEvent::ContextSwitches) if event.is_counted_on(cpu) => {
+                event.add_one();
+            }
+            #[cfg(target_arch = "riscv64")]
+            PerfEventKind::Hardware(..) => start_counter(&event),
+            _ => {}
+        }
+    }
+}
+
+/// Counts a page fault of the current thread.
+pub(crate) fn on_page_fault(ctx: &Context) {
+    let cpu = ostd::cpu::current_cpu_racy();
+    for event in events_of(ctx.posix_thread.tid()) {
+        if matches!(event.kind(), PerfEventKind::Software(SwEvent::PageFaults))
+            && event.is_counted_on(cpu)
+        {
+            event.add_one();
+        }
+    }
+}
+
+/// Takes the samples of the current thread, which has entered the kernel from the user space.
+pub(crate) fn take_samples(ctx: &Context, user_ctx: &UserContext) {
+    let cpu = ostd::cpu::current_cpu_racy();
+    for event in events_of(ctx.posix_thread.tid()) {
+        if event.is_counted_on(cpu) {
+            event.sample(ctx, user_ctx, cpu);
+        }
+    }
+}
+
+/// Enables the events of the current thread that should be enabled on `execve`.
+pub(crate) fn on_exec(ctx: &Context) {
+    for event in events_of(ctx.posix_thread.tid()) {
+        event.enable_on_exec();
+    }
+}
@@ -0,0 +1,151 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::sync::atomic::{fence, Ordering};
+
+use ostd::mm::VmIo;
+
+use crate::{
+    prelude::*,
+    vm::vmo::{Vmo, VmoOptions},
+};
+
+/// The ring buffer of a perf event, which is shared with the user space by `mmap`.
+///
+/// The first page is the metadata page (i.e., `struct perf_event_mmap_page`), which is followed
+/// by the data pages. The kernel writes the records at `data_head`, and the user space consumes
+/// them and advances `data_tail`. A record is dropped (and counted as lost) if there is not
+/// enough free space for it.
+pub(super) struct RingBuffer {
+    vmo: Vmo,
+    data_size: usize,
+    /// The position where the next record is written.
+    head: u64,
+    /// The number of the records that are dropped but not yet reported.
+    nr_lost: u64,
+}
+
+// The offsets of the fields in the metadata page.
+const DATA_HEAD_OFFSET: usize = 1024;
+const DATA_TAIL_OFFSET: usize = 1032;
+const DATA_OFFSET_OFFSET: usize = 1040;
+const DATA_SIZE_OFFSET: usize = 1048;
+
+pub(super) const PERF_RECORD_LOST: u32 = 2;
+pub(super) const PERF_RECORD_SAMPLE: u32 = 9;
+
+pub(super) const PERF_RECORD_MISC_USER: u16 = 2;
+
+impl RingBuffer {
+    /// Creates a ring buffer of the size, which must be one page plus a power of two pages.
+    pub(super) fn new(size: usize) -> Result<Self> {
+        let data_size = size.saturating_sub(PAGE_SIZE);
+        if data_size == 0 || size % PAGE_SIZE != 0 || !(data_size / PAGE_SIZE).is_power_of_two() {
+            return_errno_with_message!(Errno::EINVAL, "invalid size of the perf ring buffer");
+        }
+
+        let vmo = VmoOptions::<Rights>::new(size).alloc()?;
+        vmo.write_val(DATA_OFFSET_OFFSET, &(PAGE_SIZE as u64))?;
+        vmo.write_val(DATA_SIZE_OFFSET, &(data_size as u64))?;
+
+        Ok(Self {
+            vmo,
+            data_size,
+            head: 0,
+            nr_lost: 0,
+        })
+    }
+
+    /// Returns the VMO that backs the ring buffer.
+    pub(super) fn vmo(&self) -> &Vmo {
+        &self.vmo
+    }
+
+    /// Returns the size of the ring buffer, including the metadata page.
+    pub(super) fn size(&self) -> usize {
+        PAGE_SIZE + self.data_size
+    }
+
+    /// Returns whether some records are not yet consumed by the user space.
+    pub(super) fn has_records(&self) -> bool {
+        self.tail() != self.head
+    }
+
+    /// Writes a record, after reporting the previously dropped records of the event if any.
+    ///
+    /// The record is dropped if there is not enough free space for it.
+    pub(super) fn push(&mut self, record: &[u8], id: u64) {
+        if self.nr_lost != 0 {
+            let mut lost = RecordBuilder::new(PERF_RECORD_LOST, 0);
+            lost.push_u64(id);
+            lost.push_u64(self.nr_lost);
+            if self.write_record(&lost.finish()) {
+                self.nr_lost = 0;
+            }
+        }
+
+        if !self.write_record(record) {
+            self.nr_lost += 1;
+        }
+    }
+
+    fn write_record(&mut self, record: &[u8]) -> bool {
+        let used = self.head.wrapping_sub(self.tail());
+        if used as usize + record.len() > self.data_size {
+            return false;
+        }
+        // Do not overwrite the records before the user space consumes them.
+        fence(Ordering::Acquire);
+
+        let offset = (self.head % self.data_size as u64) as usize;
+        let first_len = record.len().min(self.data_size - offset);
+        let res = self
+            .vmo
+            .write_bytes(PAGE_SIZE + offset, &record[..first_len])
+            .and_then(|_| self.vmo.write_bytes(PAGE_SIZE, &record[first_len..]));
+        if res.is_err() {
+            return false;
+        }
+
+        // Publish the record after it is written.
+        fence(Ordering::Release);
+        self.head += record.len() as u64;
+        self.vmo.write_val(DATA_HEAD_OFFSET, &self.head).is_ok()
+    }
+
+    fn tail(&self) -> u64 {
+        self.vmo
+            .read_val::<u64>(DATA_TAIL_OFFSET)
+            .unwrap_or(self.head)
+    }
+}
+
+/// A builder of a record, which starts with `struct perf_event_header`.
+pub(super) struct RecordBuilder {
+    bytes: Vec<u8>,
+}
+
+impl RecordBuilder {
+    pub(super) fn new(type_: u32, misc: u16) -> Self {
+        let mut bytes = Vec::with_capacity(64);
+        bytes.extend_from_slice(&type_.to_ne_bytes());
+        bytes.extend_from_slice(&misc.to_ne_bytes());
+        // The size is filled in `finish`.
+        bytes.extend_from_slice(&0u16.to_ne_bytes());
+        Self { bytes }
+    }
+
+    pub(super) fn push_u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_ne_bytes());
+    }
+
+    pub(super) fn push_u32_pair(&mut self, first: u32, second: u32) {
+        self.bytes.extend_from_slice(&first.to_ne_bytes());
+        self.bytes.extend_from_slice(&second.to_ne_bytes());
+    }
+
+    pub(super) fn finish(mut self) -> Vec<u8> {
+        let size = self.bytes.len() as u16;
+        self.bytes[6..8].copy_from_slice(&size.to_ne_bytes());
+        self.bytes
+    }
+}
@@ -0,0 +1,98 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! System suspend.
+//!
+//! Suspending the system to RAM takes the following steps:
+//!  1. The user tasks are frozen, i.e., they are parked before returning to the user space,
+//!     so that they make no progress while the devices are unavailable.
+//!  2. The devices are quiesced, the CPU state is saved, and the firmware is called to enter
+//!     the sleep state by [`ostd::power::suspend_to_ram`], which returns after the system
+//!     resumes.
+//!  3. The time spent in the sleep state, which is measured by the RTC, is injected into the
+//!     real-time and boot-time clocks.
+//!  4. The user tasks are thawed.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use ostd::sync::WaitQueue;
+
+use crate::{
+    prelude::*,
+    thread::Thread,
+    time::{read_rtc_time, timekeeping},
+};
+
+/// Whether the user tasks are being frozen.
+static IS_FREEZING: AtomicBool = AtomicBool::new(false);
+/// The queue of the frozen user tasks.
+static FROZEN_TASKS: WaitQueue = WaitQueue::new();
+/// The lock that serializes the suspends.
+static SUSPEND_LOCK: Mutex<()> = Mutex::new(());
+
+/// Returns whether the system can be suspended to RAM.
+pub fn is_suspend_supported() -> bool {
+    ostd::power::is_suspend_supported()
+}
+
+/// Suspends the system to RAM, and returns after the system resumes.
+pub fn suspend_to_ram() -> Result<()> {
+    if !is_suspend_supported() {
+        return_errno_with_message!(Errno::EINVAL, "suspend-to-RAM is not supported");
+    }
+    let Some(_guard) = SUSPEND_LOCK.try_lock() else {
+        return_errno_with_message!(Errno::EBUSY, "the system is being suspended");
+    };
+
+    info!("[Power]: freezing the user tasks");
+    freeze_user_tasks();
+
+    let raw_before = timekeeping::monotonic_raw_time();
+    let rtc_before = read_rtc_time();
+    let result = ostd::power::suspend_to_ram();
+    if result.is_ok() {
+        // The raw clock may or may not advance in the sleep state, so only the part that is
+        // not counted by it is injected.
+        let raw_elapsed = timekeeping::monotonic_raw_time().saturating_sub(raw_before);
+        let rtc_elapsed = read_rtc_time().saturating_sub(rtc_before);
+        let sleep_time = rtc_elapsed.saturating_sub(raw_elapsed);
+        timekeeping::inject_sleep_time(sleep_time);
+        info!("[Power]: resumed after sleeping for {:?}", sleep_time);
+    }
+
+    thaw_user_tasks();
+
+    match result {
+        Ok(()) => Ok(()),
+        Err(ostd::Error::InvalidArgs) => {
+            return_errno_with_message!(Errno::EINVAL, "the system cannot be suspended")
+        }
+        Err(ostd::Error::IoError) => {
+            return_errno_with_message!(Errno::EIO, "the firmware fails to suspend the system")
+        }
+        Err(_) => return_errno_with_message!(Errno::EBUSY, "a device fails to suspend"),
+    }
+}
+
+/// Parks the current user task if the user tasks are being frozen.
+///
+/// This function is called before returning to the user space.
+pub(crate) fn try_to_freeze() {
+    if !IS_FREEZING.load(Ordering::Acquire) {
+        return;
+    }
+
+    FROZEN_TASKS.wait_until(|| (!IS_FREEZING.load(Ordering::Acquire)).then_some(()));
+}
+
+fn freeze_user_tasks() {
+    IS_FREEZING.store(true, Ordering::Release);
+
+    // Let the runnable user tasks reach the freezing point. The tasks that are sleeping in the
+    // kernel are frozen when they are woken up and about to return to the user space.
+    Thread::yield_now();
+}
+
+fn thaw_user_tasks() {
+    IS_FREEZING.store(false, Ordering::Release);
+    FROZEN_TASKS.wake_all();
+}
@@ -0,0 +1,388 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The `cpuset` controller, which restricts the processes to subsets of the CPUs and the
+//! memory nodes.
+//!
+//! The effective CPUs of a cgroup are the CPUs in its `cpuset.cpus` that are also effective in
+//! its parent, or all the effective CPUs of its parent if there are none. The CPU affinity of
+//! the threads is reset to the effective CPUs when the process joins the cgroup and when the
+//! effective CPUs change, and `sched_setaffinity` cannot go beyond them. The memory nodes work
+//! in the same way, but there is only one memory node since NUMA is not supported.
+//!
+//! A cgroup becomes a partition root if `root` or `isolated` is written to its
+//! `cpuset.cpus.partition`. Then its CPUs are given to it exclusively, i.e., they are taken out
+//! of the effective CPUs of its parent and the siblings. The partition is valid only if
+//!  - the parent is a partition root (the root cgroup is always one),
+//!  - its CPUs are non-empty and owned by the parent,
+//!  - its CPUs do not overlap with the valid partitions of its siblings, and
+//!  - at least one CPU is left to the parent.
+//!
+//! Otherwise, the partition is reported as invalid and the cgroup behaves as a member. The
+//! threads on the CPUs of an isolated partition are not balanced between the CPUs: they stay
+//! on the CPU where they are created or woken, if they are allowed to.
+
+use core::sync::atomic::Ordering;
+
+use ostd::cpu::{num_cpus, AtomicCpuSet, CpuId, CpuSet};
+use spin::Once;
+
+use super::{set_affinity, Cgroup, HIERARCHY_LOCK};
+use crate::prelude::*;
+
+/// A set of memory nodes, where each bit stands for a node.
+pub type NodeMask = u64;
+
+/// The number of the memory nodes.
+pub const NR_NODES: usize = 1;
+
+const ALL_NODES: NodeMask = (1 << NR_NODES) - 1;
+
+/// The kind of a cgroup in the CPU partitions (`cpuset.cpus.partition`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Partition {
+    /// The cgroup uses the CPUs of its parent.
+    Member,
+    /// The cgroup owns its CPUs.
+    Root,
+    /// The cgroup owns its CPUs, which are not balanced.
+    Isolated,
+}
+
+impl Partition {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Member => "member",
+            Self::Root => "root",
+            Self::Isolated => "isolated",
+        }
+    }
+}
+
+impl core::str::FromStr for Partition {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "member" => Ok(Self::Member),
+            "root" => Ok(Self::Root),
+            "isolated" => Ok(Self::Isolated),
+            _ => return_errno_with_message!(Errno::EINVAL, "invalid partition type"),
+        }
+    }
+}
+
+/// The state of the `cpuset` controller of a cgroup.
+pub struct Cpuset {
+    state: Mutex<CpusetState>,
+}
+
+struct CpusetState {
+    /// The CPUs in `cpuset.cpus`, or `None` if it is empty.
+    cpus: Option<CpuSet>,
+    /// The memory nodes in `cpuset.mems`, or `None` if it is empty.
+    mems: Option<NodeMask>,
+    partition: Partition,
+    is_partition_valid: bool,
+    effective_cpus: CpuSet,
+    effective_mems: NodeMask,
+}
+
+impl Cpuset {
+    pub(super) fn new_root() -> Self {
+        Self::new(CpuSet::new_full(), ALL_NODES)
+    }
+
+    pub(super) fn new_child(parent: &Cpuset) -> Self {
+        let parent = parent.state.lock();
+        Self::new(parent.effective_cpus.clone(), parent.effective_mems)
+    }
+
+    fn new(effective_cpus: CpuSet, effective_mems: NodeMask) -> Self {
+        Self {
+            state: Mutex::new(CpusetState {
+                cpus: None,
+                mems: None,
+                partition: Partition::Member,
+                is_partition_valid: true,
+                effective_cpus,
+                effective_mems,
+            }),
+        }
+    }
+
+    /// Returns the CPUs in `cpuset.cpus`.
+    pub fn cpus(&self) -> CpuSet {
+        self.state
+            .lock()
+            .cpus
+            .clone()
+            .unwrap_or_else(CpuSet::new_empty)
+    }
+
+    /// Sets `cpuset.cpus`.
+    pub fn set_cpus(&self, cpus: CpuSet) -> Result<()> {
+        let _guard = HIERARCHY_LOCK.lock();
+
+        self.state.lock().cpus = Some(cpus).filter(|cpus| !cpus.is_empty());
+        update_hierarchy();
+        Ok(())
+    }
+
+    /// Returns the CPUs that the processes are allowed to run on.
+    pub fn effective_cpus(&self) -> CpuSet {
+        self.state.lock().effective_cpus.clone()
+    }
+
+    /// Returns the memory nodes in `cpuset.mems`.
+    pub fn mems(&self) -> NodeMask {
+        self.state.lock().mems.unwrap_or(0)
+    }
+
+    /// Sets `cpuset.mems`.
+    pub fn set_mems(&self, mems: NodeMask) -> Result<()> {
+        let _guard = HIERARCHY_LOCK.lock();
+
+        if mems & !ALL_NODES != 0 {
+            return_errno_with_message!(Errno::EINVAL, "the memory node does not exist");
+        }
+        self.state.lock().mems = Some(mems).filter(|mems| *mems != 0);
+        update_hierarchy();
+        Ok(())
+    }
+
+    /// Returns the memory nodes that the processes are allowed to allocate memory from.
+    pub fn effective_mems(&self) -> NodeMask {
+        self.state.lock().effective_mems
+    }
+
+    /// Returns the kind of the cgroup in the CPU partitions, and whether it is valid.
+    pub fn partition(&self) -> (Partition, bool) {
+        let state = self.state.lock();
+        (state.partition, state.is_partition_valid)
+    }
+
+    /// Sets `cpuset.cpus.partition`.
+    pub fn set_partition(&self, partition: Partition) -> Result<()> {
+        let _guard = HIERARCHY_LOCK.lock();
+
+        self.state.lock().partition = partition;
+        update_hierarchy();
+        Ok(())
+    }
+}
+
+/// Returns the CPUs that belong to the isolated partitions.
+pub fn isolated_cpus() -> &'static AtomicCpuSet {
+    static ISOLATED_CPUS: Once<AtomicCpuSet> = Once::new();
+
+    ISOLATED_CPUS.call_once(|| AtomicCpuSet::new(CpuSet::new_empty()))
+}
+
+/// Returns whether the CPU belongs to an isolated partition.
+pub fn is_isolated_cpu(cpu: CpuId) -> bool {
+    isolated_cpus().contains(cpu, Ordering::Relaxed)
+}
+
+/// Restricts the CPU affinity requested by `sched_setaffinity` to the effective CPUs of the
+/// cgroup.
+pub fn restrict_affinity(cgroup: &Cgroup, cpus: &CpuSet) -> Result<CpuSet> {
+    let effective_cpus = cgroup.cpuset().effective_cpus();
+    let cpus = intersection(cpus, &effective_cpus);
+    if cpus.is_empty() {
+        return_errno_with_message!(Errno::EINVAL, "the CPUs are not allowed by the cpuset");
+    }
+    Ok(cpus)
+}
+
+/// Recomputes the effective CPUs, the effective memory nodes and the partitions of all the
+/// cgroups.
+///
+/// The caller must hold [`HIERARCHY_LOCK`].
+pub(super) fn update_hierarchy() {
+    let mut isolated = CpuSet::new_empty();
+    update_subtree(
+        Cgroup::root(),
+        CpuSet::new_full(),
+        true,
+        ALL_NODES,
+        &mut isolated,
+    );
+    isolated_cpus().store(&isolated);
+}
+
+/// Updates the cgroup and its descendants.
+///
+/// The `cpus` are owned by the cgroup, of which the `is_partition_root` cgroup gives
+/// the CPUs of the valid partitions to the children.
+fn update_subtree(
+    cgroup: &Cgroup,
+    cpus: CpuSet,
+    is_partition_root: bool,
+    mems: NodeMask,
+    isolated: &mut CpuSet,
+) {
+    let children = cgroup.children();
+    let is_enabled = cgroup.is_cpuset_enabled();
+
+    let mut claimed = CpuSet::new_empty();
+    let mut is_valid_partitions = Vec::with_capacity(children.len());
+    for child in children.iter() {
+        let state = child.cpuset.state.lock();
+        let is_valid = is_enabled
+            && is_partition_root
+            && state.partition != Partition::Member
+            && state.cpus.as_ref().is_some_and(|child_cpus| {
+                is_subset(child_cpus, &cpus)
+                    && intersection(child_cpus, &claimed).is_empty()
+                    && difference(&difference(&cpus, &claimed), child_cpus).count() > 0
+            });
+        if is_valid {
+            claimed = union(&claimed, state.cpus.as_ref().unwrap());
+        }
+        is_valid_partitions.push(is_valid);
+    }
+
+    let effective_cpus = difference(&cpus, &claimed);
+    let is_changed = {
+        let mut state = cgroup.cpuset.state.lock();
+        let is_changed = !state.effective_cpus.iter().eq(effective_cpus.iter());
+        state.effective_cpus = effective_cpus.clone();
+        state.effective_mems = mems;
+        is_changed
+    };
+    if is_changed {
+        for process in cgroup.processes() {
+            set_affinity(&process, &effective_cpus);
+        }
+    }
+
+    for (child, is_valid) in children.iter().zip(is_valid_partitions) {
+        let (child_cpus, child_mems) = {
+            let mut state = child.cpuset.state.lock();
+            state.is_partition_valid = is_valid || state.partition == Partition::Member;
+
+            let child_mems = state
+                .mems
+                .filter(|_| is_enabled)
+                .map(|child_mems| child_mems & mems)
+                .filter(|child_mems| *child_mems != 0)
+                .unwrap_or(mems);
+            let child_cpus = if is_valid {
+                let child_cpus = state.cpus.clone().unwrap();
+                if state.partition == Partition::Isolated {
+                    *isolated = union(isolated, &child_cpus);
+                }
+                child_cpus
+            } else {
+                state
+                    .cpus
+                    .as_ref()
+                    .filter(|_| is_enabled)
+                    .map(|child_cpus| intersection(child_cpus, &effective_cpus))
+                    .filter(|child_cpus| !child_cpus.is_empty())
+                    .unwrap_or_else(|| effective_cpus.clone())
+            };
+            (child_cpus, child_mems)
+        };
+        update_subtree(child, child_cpus, is_valid, child_mems, isolated);
+    }
+}
+
+fn intersection(a: &CpuSet, b: &CpuSet) -> CpuSet {
+    let mut result = CpuSet::new_empty();
+    a.iter()
+        .filter(|cpu| b.contains(*cpu))
+        .for_each(|cpu| result.add(cpu));
+    result
+}
+
+fn union(a: &CpuSet, b: &CpuSet) -> CpuSet {
+    let mut result = a.clone();
+    b.iter().for_each(|cpu| result.add(cpu));
+    result
+}
+
+fn difference(a: &CpuSet, b: &CpuSet) -> CpuSet {
+    let mut result = a.clone();
+    b.iter().for_each(|cpu| result.remove(cpu));
+    result
+}
+
+fn is_subset(a: &CpuSet, b: &CpuSet) -> bool {
+    a.iter().all(|cpu| b.contains(cpu))
+}
+
+/// Parses a list of IDs like `0-3,5`, where each ID must be less than `limit`.
+pub fn parse_list(list: &str, limit: usize) -> Result<Vec<usize>> {
+    let parse_id = |id: &str| -> Result<usize> {
+        match id.trim().parse::<usize>() {
+            Ok(id) if id < limit => Ok(id),
+            _ => return_errno_with_message!(Errno::EINVAL, "invalid ID in the list"),
+        }
+    };
+
+    let mut ids = Vec::new();
+    for range in list.trim().split(',').filter(|range| !range.is_empty()) {
+        let (start, end) = match range.split_once('-') {
+            Some((start, end)) => (parse_id(start)?, parse_id(end)?),
+            None => {
+                let id = parse_id(range)?;
+                (id, id)
+            }
+        };
+        if start > end {
+            return_errno_with_message!(Errno::EINVAL, "invalid range in the list");
+        }
+        ids.extend(start..=end);
+    }
+    Ok(ids)
+}
+
+/// Formats the sorted IDs as a list like `0-3,5`.
+pub fn format_list(ids: impl Iterator<Item = usize>) -> String {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for id in ids {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == id => *end = id,
+            _ => ranges.push((id, id)),
+        }
+    }
+
+    let ranges: Vec<String> = ranges
+        .into_iter()
+        .map(|(start, end)| {
+            if start == end {
+                start.to_string()
+            } else {
+                alloc::format!("{}-{}", start, end)
+            }
+        })
+        .collect();
+    ranges.join(",")
+}
+
+/// Parses a list of CPUs.
+pub fn parse_cpu_list(list: &str) -> Result<CpuSet> {
+    let mut cpus = CpuSet::new_empty();
+    for id in parse_list(list, num_cpus())? {
+        cpus.add(CpuId::try_from(id).unwrap());
+    }
+    Ok(cpus)
+}
+
+/// Formats the CPUs as a list.
+pub fn format_cpu_list(cpus: &CpuSet) -> String {
+    format_list(cpus.iter().map(|cpu| cpu.as_usize()))
+}
+
+/// Parses a list of memory nodes.
+pub fn parse_node_list(list: &str) -> Result<NodeMask> {
+    Ok(parse_list(list, NR_NODES)?
+        .into_iter()
+        .fold(0, |mask, node| mask | (1 << node)))
+}
+
+/// Formats the memory nodes as a list.
+pub fn format_node_list(nodes: NodeMask) -> String {
+    format_list((0..NR_NODES).filter(|node| nodes & (1 << node) != 0))
+}
@@ -0,0 +1,225 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Control groups (cgroups) of the unified hierarchy, i.e., cgroup v2.
+//!
+//! Each process belongs to exactly one cgroup, which is inherited from its parent when the
+//! process is created. A process is moved into a cgroup by writing its PID to the
+//! `cgroup.procs` file of the cgroup. The hierarchy is managed with the cgroup2 file system
+//! (see [`crate::fs::cgroupfs`]).
+//!
+//! Only the `cpuset` controller is available (see [`cpuset`]). Like Linux, the processes can
+//! only be in the root cgroup or in the cgroups that do not enable the controller for their
+//! children (the "no internal process" constraint).
+//!
+//! Reference: <https://docs.kernel.org/admin-guide/cgroup-v2.html>.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use spin::Once;
+
+use self::cpuset::Cpuset;
+use super::{process_table, Process};
+use crate::{prelude::*, thread::AsThread};
+
+pub mod cpuset;
+
+/// Serializes the changes to the hierarchy, the memberships and the controllers.
+static HIERARCHY_LOCK: Mutex<()> = Mutex::new(());
+
+/// A control group.
+pub struct Cgroup {
+    name: String,
+    parent: Option<Arc<Cgroup>>,
+    children: Mutex<BTreeMap<String, Arc<Cgroup>>>,
+    /// Whether the `cpuset` controller is enabled for the children.
+    is_cpuset_enabled: AtomicBool,
+    cpuset: Cpuset,
+}
+
+impl Cgroup {
+    /// Returns the root cgroup.
+    pub fn root() -> &'static Arc<Cgroup> {
+        static ROOT: Once<Arc<Cgroup>> = Once::new();
+
+        ROOT.call_once(|| {
+            Arc::new(Self {
+                name: String::new(),
+                parent: None,
+                children: Mutex::new(BTreeMap::new()),
+                is_cpuset_enabled: AtomicBool::new(false),
+                cpuset: Cpuset::new_root(),
+            })
+        })
+    }
+
+    /// Returns the name of the cgroup, which is empty for the root cgroup.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the parent cgroup, or `None` for the root cgroup.
+    pub fn parent(&self) -> Option<&Arc<Cgroup>> {
+        self.parent.as_ref()
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.parent.is_none()
+    }
+
+    /// Returns the path of the cgroup in the hierarchy, e.g., `/` or `/foo/bar`.
+    pub fn path(&self) -> String {
+        let Some(parent) = self.parent.as_ref() else {
+            return String::from("/");
+        };
+        let mut path = parent.path();
+        if !parent.is_root() {
+            path.push('/');
+        }
+        path.push_str(&self.name);
+        path
+    }
+
+    /// Returns the child cgroups, in the order of their names.
+    pub fn children(&self) -> Vec<Arc<Cgroup>> {
+        self.children.lock().values().cloned().collect()
+    }
+
+    /// Returns the child cgroup with the name.
+    pub fn child(&self, name: &str) -> Option<Arc<Cgroup>> {
+        self.children.lock().get(name).cloned()
+    }
+
+    /// Creates a child cgroup.
+    pub fn create_child(self: &Arc<Self>, name: &str) -> Result<Arc<Cgroup>> {
+        let _guard = HIERARCHY_LOCK.lock();
+
+        let mut children = self.children.lock();
+        if children.contains_key(name) {
+            return_errno_with_message!(Errno::EEXIST, "the cgroup already exists");
+        }
+        let child = Arc::new(Self {
+            name: name.to_string(),
+            parent: Some(self.clone()),
+            children: Mutex::new(BTreeMap::new()),
+            is_cpuset_enabled: AtomicBool::new(false),
+            cpuset: Cpuset::new_child(&self.cpuset),
+        });
+        children.insert(name.to_string(), child.clone());
+        Ok(child)
+    }
+
+    /// Removes a child cgroup, which must have neither child cgroups nor processes.
+    pub fn remove_child(&self, name: &str) -> Result<()> {
+        let _guard = HIERARCHY_LOCK.lock();
+
+        let mut children = self.children.lock();
+        let Some(child) = children.get(name) else {
+            return_errno_with_message!(Errno::ENOENT, "the cgroup does not exist");
+        };
+        if !child.children.lock().is_empty() || child.has_processes() {
+            return_errno_with_message!(Errno::EBUSY, "the cgroup is in use");
+        }
+        children.remove(name);
+        drop(children);
+
+        // The CPUs of the removed partition are given back to the parent.
+        cpuset::update_hierarchy();
+        Ok(())
+    }
+
+    /// Returns the processes in the cgroup, excluding the zombies.
+    pub fn processes(&self) -> Vec<Arc<Process>> {
+        process_table::process_table_mut()
+            .iter()
+            .filter(|process| {
+                !process.status().is_zombie() && core::ptr::eq(&*process.cgroup(), self)
+            })
+            .cloned()
+            .collect()
+    }
+
+    fn has_processes(&self) -> bool {
+        !self.processes().is_empty()
+    }
+
+    /// Moves the process into the cgroup.
+    ///
+    /// The CPU affinity of the threads of the process is reset to the effective CPUs of the
+    /// cgroup.
+    pub fn attach(self: &Arc<Self>, process: &Arc<Process>) -> Result<()> {
+        let _guard = HIERARCHY_LOCK.lock();
+
+        if !self.is_root() && self.is_cpuset_enabled() {
+            return_errno_with_message!(
+                Errno::EBUSY,
+                "the cgroup enables controllers for its children"
+            );
+        }
+        process.set_cgroup(self.clone());
+        set_affinity(process, &self.cpuset.effective_cpus());
+        Ok(())
+    }
+
+    /// Returns the names of the controllers that are available in the cgroup.
+    pub fn controllers(&self) -> &'static [&'static str] {
+        match self.parent.as_ref() {
+            Some(parent) if !parent.is_cpuset_enabled() => &[],
+            _ => &["cpuset"],
+        }
+    }
+
+    /// Returns whether the `cpuset` controller is enabled for the children.
+    pub fn is_cpuset_enabled(&self) -> bool {
+        self.is_cpuset_enabled.load(Ordering::Relaxed)
+    }
+
+    /// Enables or disables the `cpuset` controller for the children, which is done by writing
+    /// `+cpuset` or `-cpuset` to `cgroup.subtree_control`.
+    pub fn set_cpuset_enabled(&self, is_enabled: bool) -> Result<()> {
+        let _guard = HIERARCHY_LOCK.lock();
+
+        if is_enabled == self.is_cpuset_enabled() {
+            return Ok(());
+        }
+        if is_enabled {
+            if self.controllers().is_empty() {
+                return_errno_with_message!(Errno::ENOENT, "the controller is not available");
+            }
+            if !self.is_root() && self.has_processes() {
+                return_errno_with_message!(Errno::EBUSY, "the cgroup has processes");
+            }
+        } else if self
+            .children
+            .lock()
+            .values()
+            .any(|child| child.is_cpuset_enabled())
+        {
+            return_errno_with_message!(Errno::EBUSY, "the controller is used by the children");
+        }
+
+        self.is_cpuset_enabled.store(is_enabled, Ordering::Relaxed);
+        cpuset::update_hierarchy();
+        Ok(())
+    }
+
+    pub fn cpuset(&self) -> &Cpuset {
+        &self.cpuset
+    }
+}
+
+impl Debug for Cgroup {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Cgroup")
+            .field("path", &self.path())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Sets the CPU affinity of all the threads of the process.
+fn set_affinity(process: &Process, cpus: &ostd::cpu::CpuSet) {
+    for task in process.tasks().lock().as_slice() {
+        if let Some(thread) = task.as_thread() {
+            thread.atomic_cpu_affinity().store(cpus);
+        }
+    }
+}
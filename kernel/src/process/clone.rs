@@ -5,11 +5,12 @@ use core::{num::NonZeroU64, sync::atomic::Ordering};
 use ostd::{cpu::context::UserContext, sync::RwArc, task::Task, user::UserContextApi};
 
 use super::{
-    posix_thread::{AsPosixThread, PosixThreadBuilder, ThreadName},
+    credentials::capabilities::CapSet,
+    posix_thread::{AsPosixThread, PosixThreadBuilder, RseqArea, ThreadLocal, ThreadName},
     process_table,
     process_vm::ProcessVm,
     signal::{constants::SIGCHLD, sig_disposition::SigDispositions, sig_num::SigNum},
-    Credentials, Process, ProcessBuilder,
+    Credentials, Process, ProcessBuilder, ResourceType,
 };
 use crate::{
     cpu::LinuxAbi,
@@ -17,6 +18,7 @@ use crate::{
     fs::{file_table::FileTable, thread_info::ThreadFsInfo},
     prelude::*,
     process::posix_thread::allocate_posix_tid,
+    security::hooks,
     thread::{AsThread, Tid},
 };
 
@@ -186,6 +188,7 @@ pub fn clone_child(
     clone_args: CloneArgs,
 ) -> Result<Tid> {
     clone_args.flags.check_unsupported_flags()?;
+    hooks::task_create(clone_args.flags)?;
     if clone_args.flags.contains(CloneFlags::CLONE_THREAD) {
         let child_task = clone_child_task(ctx, parent_context, clone_args)?;
         let child_thread = child_task.as_thread().unwrap();
@@ -266,7 +269,8 @@ fn clone_child_task(
             .process(posix_thread.weak_process())
             .sig_mask(sig_mask)
             .file_table(child_file_table)
-            .fs(child_fs);
+            .fs(child_fs)
+            .keyrings(posix_thread.keyrings().new_child(true));
 
         // Deal with SETTID/CLEARTID flags
         clone_parent_settid(child_tid, clone_args.parent_tid, clone_flags)?;
@@ -299,6 +303,8 @@ fn clone_child_process(
 
     let clone_flags = clone_args.flags;
 
+    check_nproc_limit(ctx)?;
+
     // clone vm
     let child_process_vm = {
         let parent_process_vm = process.vm();
@@ -349,6 +355,8 @@ fn clone_child_process(
                 .sig_mask(child_sig_mask)
                 .file_table(child_file_table)
                 .fs(child_fs)
+                .keyrings(posix_thread.keyrings().new_child(false))
+                .rseq_area(clone_rseq(thread_local, clone_flags))
         };
 
         // Deal with SETTID/CLEARTID flags
@@ -365,7 +373,8 @@ fn clone_child_process(
             .main_thread_builder(child_thread_builder)
             .process_vm(child_process_vm)
             .sig_dispositions(child_sig_dispositions)
-            .nice(child_nice);
+            .nice(child_nice)
+            .resource_limits(process.resource_limits().clone());
 
         process_builder.build()?
     };
@@ -384,9 +393,46 @@ fn clone_child_process(
         child.has_child_subreaper.store(true, Ordering::Relaxed);
     }
 
+    if process.is_syscall_traced() {
+        child.set_syscall_traced(true);
+    }
+
     Ok(child)
 }
 
+/// Checks whether the real user of the current thread can have more processes under the
+/// `RLIMIT_NPROC` of the current process.
+fn check_nproc_limit(ctx: &Context) -> Result<()> {
+    let (ruid, is_privileged) = {
+        let credentials = ctx.posix_thread.credentials();
+        let is_privileged = credentials
+            .effective_capset()
+            .intersects(CapSet::SYS_RESOURCE | CapSet::SYS_ADMIN);
+        (credentials.ruid(), is_privileged)
+    };
+    if ruid.is_root() || is_privileged {
+        return Ok(());
+    }
+
+    let rlimit_nproc = ctx
+        .process
+        .resource_limits()
+        .get_rlimit(ResourceType::RLIMIT_NPROC)
+        .get_cur();
+    let nr_processes = process_table::process_table_mut()
+        .iter()
+        .filter(|process| {
+            let main_thread = process.main_thread();
+            let posix_thread = main_thread.as_posix_thread().unwrap();
+            posix_thread.credentials().ruid() == ruid
+        })
+        .count();
+    if nr_processes as u64 >= rlimit_nproc {
+        return_errno_with_message!(Errno::EAGAIN, "the process limit is reached");
+    }
+    Ok(())
+}
+
 fn clone_child_cleartid(
     child_builder: PosixThreadBuilder,
     child_tidptr: Vaddr,
@@ -503,6 +549,18 @@ fn clone_sighand(
     }
 }
 
+/// Clones the rseq area, which is inherited only if the child has its own copy of the memory.
+///
+/// Like `rseq_fork` in Linux, the registration is dropped for every `CLONE_VM` child
+/// (including a vforked one), since the `struct rseq` area of the parent must not be shared.
+fn clone_rseq(parent_thread_local: &ThreadLocal, clone_flags: CloneFlags) -> Option<RseqArea> {
+    if clone_flags.contains(CloneFlags::CLONE_VM) {
+        None
+    } else {
+        parent_thread_local.rseq().area()
+    }
+}
+
 fn clone_sysvsem(clone_flags: CloneFlags) -> Result<()> {
     if clone_flags.contains(CloneFlags::CLONE_SYSVSEM) {
         warn!("CLONE_SYSVSEM is not supported now");
@@ -8,6 +8,7 @@ use super::{group::AtomicGid, user::AtomicUid, Gid, Uid};
 use crate::{
     prelude::*,
     process::credentials::capabilities::{AtomicCapSet, CapSet},
+    security::{landlock::LandlockDomain, seccomp::SeccompMode},
 };
 
 #[derive(Debug)]
@@ -49,6 +50,15 @@ pub(super) struct Credentials_ {
 
     /// Keep capabilities flag
     keep_capabilities: AtomicBool,
+
+    /// Whether executing a program can never grant more privileges, e.g., by the set-user-ID bit.
+    no_new_privs: AtomicBool,
+
+    /// The Landlock rulesets that are enforced.
+    landlock_domain: RwLock<Option<Arc<LandlockDomain>>>,
+
+    /// The seccomp mode, with the installed filters if any.
+    seccomp_mode: RwLock<SeccompMode>,
 }
 
 impl Credentials_ {
@@ -71,6 +81,9 @@ impl Credentials_ {
             permitted_capset: AtomicCapSet::new(capset),
             effective_capset: AtomicCapSet::new(capset),
             keep_capabilities: AtomicBool::new(false),
+            no_new_privs: AtomicBool::new(false),
+            landlock_domain: RwLock::new(None),
+            seccomp_mode: RwLock::new(SeccompMode::Disabled),
         }
     }
 
@@ -100,6 +113,18 @@ impl Credentials_ {
         self.keep_capabilities.load(Ordering::Relaxed)
     }
 
+    pub(super) fn no_new_privs(&self) -> bool {
+        self.no_new_privs.load(Ordering::Relaxed)
+    }
+
+    pub(super) fn landlock_domain(&self) -> Option<Arc<LandlockDomain>> {
+        self.landlock_domain.read().clone()
+    }
+
+    pub(super) fn seccomp_mode(&self) -> SeccompMode {
+        self.seccomp_mode.read().clone()
+    }
+
     pub(super) fn set_uid(&self, uid: Uid) {
         if self.is_privileged() {
             self.ruid.store(uid, Ordering::Relaxed);
@@ -353,6 +378,18 @@ impl Credentials_ {
             .store(keep_capabilities, Ordering::Relaxed);
     }
 
+    pub(super) fn set_no_new_privs(&self) {
+        self.no_new_privs.store(true, Ordering::Relaxed);
+    }
+
+    pub(super) fn set_landlock_domain(&self, domain: Arc<LandlockDomain>) {
+        *self.landlock_domain.write() = Some(domain);
+    }
+
+    pub(super) fn set_seccomp_mode(&self, mode: SeccompMode) {
+        *self.seccomp_mode.write() = mode;
+    }
+
     // For `setregid`, rgid can *NOT* be set to old sgid,
     // while for `setresgid`, ruid can be set to old sgid.
     fn check_gid_perm(
@@ -472,6 +509,9 @@ impl Clone for Credentials_ {
             permitted_capset: self.permitted_capset.clone(),
             effective_capset: self.effective_capset.clone(),
             keep_capabilities: AtomicBool::new(self.keep_capabilities.load(Ordering::Relaxed)),
+            no_new_privs: AtomicBool::new(self.no_new_privs.load(Ordering::Relaxed)),
+            landlock_domain: RwLock::new(self.landlock_domain.read().clone()),
+            seccomp_mode: RwLock::new(self.seccomp_mode.read().clone()),
         }
     }
 }
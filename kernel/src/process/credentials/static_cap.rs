@@ -5,7 +5,10 @@ use aster_rights_proc::require;
 use ostd::sync::{PreemptDisabled, RwLockReadGuard, RwLockWriteGuard};
 
 use super::{capabilities::CapSet, credentials_::Credentials_, Credentials, Gid, Uid};
-use crate::prelude::*;
+use crate::{
+    prelude::*,
+    security::{landlock::LandlockDomain, seccomp::SeccompMode},
+};
 
 impl<R: TRights> Credentials<R> {
     /// Creates a root `Credentials`. This method can only be used when creating the first process
@@ -85,6 +88,30 @@ impl<R: TRights> Credentials<R> {
         self.0.keep_capabilities()
     }
 
+    /// Gets the no-new-privileges flag.
+    ///
+    /// This method requires the `Read` right.
+    #[require(R > Read)]
+    pub fn no_new_privs(&self) -> bool {
+        self.0.no_new_privs()
+    }
+
+    /// Gets the enforced Landlock rulesets.
+    ///
+    /// This method requires the `Read` right.
+    #[require(R > Read)]
+    pub fn landlock_domain(&self) -> Option<Arc<LandlockDomain>> {
+        self.0.landlock_domain()
+    }
+
+    /// Gets the seccomp mode.
+    ///
+    /// This method requires the `Read` right.
+    #[require(R > Read)]
+    pub fn seccomp_mode(&self) -> SeccompMode {
+        self.0.seccomp_mode()
+    }
+
     /// Sets uid. If self is privileged, sets the effective, real, saved-set user ids as `uid`,
     /// Otherwise, sets effective user id as `uid`.
     ///
@@ -153,6 +180,30 @@ impl<R: TRights> Credentials<R> {
         self.0.set_keep_capabilities(keep_capabilities);
     }
 
+    /// Sets the no-new-privileges flag, which cannot be cleared.
+    ///
+    /// This method requires the `Write` right.
+    #[require(R > Write)]
+    pub fn set_no_new_privs(&self) {
+        self.0.set_no_new_privs();
+    }
+
+    /// Sets the enforced Landlock rulesets.
+    ///
+    /// This method requires the `Write` right.
+    #[require(R > Write)]
+    pub fn set_landlock_domain(&self, domain: Arc<LandlockDomain>) {
+        self.0.set_landlock_domain(domain);
+    }
+
+    /// Sets the seccomp mode.
+    ///
+    /// This method requires the `Write` right.
+    #[require(R > Write)]
+    pub fn set_seccomp_mode(&self, mode: SeccompMode) {
+        self.0.set_seccomp_mode(mode);
+    }
+
     // *********** Gid methods **********
 
     /// Gets real group id.
@@ -3,7 +3,10 @@
 use core::sync::atomic::Ordering;
 
 use super::{posix_thread::ThreadLocal, process_table, Pid, Process};
-use crate::{prelude::*, process::signal::signals::kernel::KernelSignal};
+use crate::{
+    ipc::semaphore::system_v::sem_set::undo_sem_adjustments, prelude::*,
+    process::signal::signals::kernel::KernelSignal,
+};
 
 /// Exits the current POSIX process.
 ///
@@ -16,6 +19,8 @@ pub(super) fn exit_process(thread_local: &ThreadLocal, current_process: &Process
     current_process.status().set_zombie();
     current_process.status().set_vfork_child(false);
 
+    undo_sem_adjustments(current_process.pid());
+
     // FIXME: This is obviously wrong in a number of ways, since different threads can have
     // different file tables, and different processes can share the same file table.
     thread_local.file_table().borrow().write().close_all();
@@ -1,5 +1,6 @@
 // SPDX-License-Identifier: MPL-2.0
 
+pub mod cgroup;
 mod clone;
 pub mod credentials;
 mod exit;
@@ -11,6 +12,7 @@ mod process_filter;
 pub mod process_table;
 mod process_vm;
 mod program_loader;
+mod ptrace_access;
 pub mod rlimit;
 pub mod signal;
 mod status;
@@ -23,13 +25,16 @@ pub use clone::{clone_child, CloneArgs, CloneFlags};
 pub use credentials::{Credentials, Gid, Uid};
 pub use kill::{kill, kill_all, kill_group, tgkill};
 pub use process::{
-    ExitCode, JobControl, Pgid, Pid, Process, ProcessBuilder, ProcessGroup, Session, Sid, Terminal,
+    ExitCode, JobControl, Pgid, Pid, PosixTimer, Process, ProcessBuilder, ProcessGroup, Session,
+    Sid, Terminal, TimerNotify,
 };
 pub use process_filter::ProcessFilter;
 pub use process_vm::{
-    renew_vm_and_map, MAX_ARGV_NUMBER, MAX_ARG_LEN, MAX_ENVP_NUMBER, MAX_ENV_LEN,
+    randomize_va_space, renew_vm_and_map, set_randomize_va_space, MmLayout, RandomizeVaSpace,
+    MAX_ARGV_NUMBER, MAX_ARG_LEN, MAX_ENVP_NUMBER, MAX_ENV_LEN,
 };
-pub use program_loader::{check_executable_file, ProgramToLoad};
+pub use program_loader::{binfmt_misc, check_executable_file, ProgramToLoad};
+pub use ptrace_access::check_ptrace_access;
 pub use rlimit::ResourceType;
 pub use term_status::TermStatus;
 pub use wait::{wait_child_exit, WaitOptions};
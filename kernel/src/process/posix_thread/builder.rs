@@ -8,7 +8,7 @@ use ostd::{
     task::Task,
 };
 
-use super::{thread_table, PosixThread, ThreadLocal};
+use super::{thread_table, CpuTimeAccount, PosixThread, RseqArea, ThreadLocal};
 use crate::{
     fs::{file_table::FileTable, thread_info::ThreadFsInfo},
     prelude::*,
@@ -18,6 +18,7 @@ use crate::{
         Credentials, Process,
     },
     sched::{Nice, SchedPolicy},
+    security::keys::ThreadKeyrings,
     thread::{task, Thread, Tid},
     time::{clocks::ProfClock, TimerManager},
 };
@@ -39,6 +40,8 @@ pub struct PosixThreadBuilder {
     sig_mask: AtomicSigMask,
     sig_queues: SigQueues,
     sched_policy: SchedPolicy,
+    keyrings: Option<ThreadKeyrings>,
+    rseq_area: Option<RseqArea>,
 }
 
 impl PosixThreadBuilder {
@@ -56,6 +59,8 @@ impl PosixThreadBuilder {
             sig_mask: AtomicSigMask::new_empty(),
             sig_queues: SigQueues::new(),
             sched_policy: SchedPolicy::Fair(Nice::default()),
+            keyrings: None,
+            rseq_area: None,
         }
     }
 
@@ -99,6 +104,16 @@ impl PosixThreadBuilder {
         self
     }
 
+    pub fn keyrings(mut self, keyrings: ThreadKeyrings) -> Self {
+        self.keyrings = Some(keyrings);
+        self
+    }
+
+    pub fn rseq_area(mut self, rseq_area: Option<RseqArea>) -> Self {
+        self.rseq_area = rseq_area;
+        self
+    }
+
     pub fn build(self) -> Arc<Task> {
         let Self {
             tid,
@@ -113,12 +128,16 @@ impl PosixThreadBuilder {
             sig_mask,
             sig_queues,
             sched_policy,
+            keyrings,
+            rseq_area,
         } = self;
 
         let file_table = file_table.unwrap_or_else(|| RwArc::new(FileTable::new_with_stdio()));
 
         let fs = fs.unwrap_or_else(|| Arc::new(ThreadFsInfo::default()));
 
+        let keyrings = keyrings.unwrap_or_default();
+
         Arc::new_cyclic(|weak_task| {
             let root_vmar = process
                 .upgrade()
@@ -134,18 +153,23 @@ impl PosixThreadBuilder {
                     tid,
                     name: Mutex::new(thread_name),
                     credentials,
+                    keyrings,
                     file_table: file_table.clone_ro(),
                     fs,
                     sig_mask,
                     sig_queues,
                     signalled_waker: SpinLock::new(None),
                     prof_clock,
+                    cpu_time_account: CpuTimeAccount::new(),
                     virtual_timer_manager,
                     prof_timer_manager,
                 }
             };
 
-            let cpu_affinity = CpuSet::new_full();
+            // The threads are restricted to the CPUs of the cpuset.
+            let cpu_affinity = process.upgrade().map_or_else(CpuSet::new_full, |process| {
+                process.cgroup().cpuset().effective_cpus()
+            });
             let thread = Arc::new(Thread::new(
                 weak_task.clone(),
                 posix_thread,
@@ -153,8 +177,13 @@ impl PosixThreadBuilder {
                 sched_policy,
             ));
 
-            let thread_local =
-                ThreadLocal::new(set_child_tid, clear_child_tid, root_vmar, file_table);
+            let thread_local = ThreadLocal::new(
+                set_child_tid,
+                clear_child_tid,
+                root_vmar,
+                file_table,
+                rseq_area,
+            );
 
             thread_table::add_thread(tid, thread.clone());
             task::create_new_user_task(user_ctx, thread, thread_local)
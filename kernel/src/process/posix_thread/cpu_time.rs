@@ -0,0 +1,175 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! CPU time accounting of POSIX threads.
+//!
+//! The CPU time is measured with the TSC (the `time` CSR on RISC-V) when a thread enters or
+//! leaves the user mode, when a context switch happens, and at the timer interrupts. So the
+//! accounting does not rely on the timer interrupts and is precise to the TSC cycles.
+//! The time spent on handling interrupts is excluded from the CPU time of the threads.
+
+use core::{
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::Duration,
+};
+
+use ostd::{
+    arch::{read_tsc, tsc_freq},
+    cpu::{CpuId, PinCurrentCpu},
+    cpu_local,
+    task::disable_preempt,
+    trap::{current_irq_cycles, hardirq_cycles, softirq_cycles},
+};
+
+use super::{AsPosixThread, PosixThread};
+use crate::{prelude::*, process::Process, thread::Thread};
+
+/// The state of the CPU time accounting of a POSIX thread.
+pub struct CpuTimeAccount {
+    /// The TSC value when the CPU time was accounted last time.
+    last_cycles: AtomicU64,
+    /// The interrupt cycles of the CPU when the CPU time was accounted last time.
+    last_irq_cycles: AtomicU64,
+    /// Whether the thread is running in the user mode.
+    is_in_user_mode: AtomicBool,
+}
+
+impl CpuTimeAccount {
+    pub(super) fn new() -> Self {
+        Self {
+            last_cycles: AtomicU64::new(read_tsc()),
+            last_irq_cycles: AtomicU64::new(current_irq_cycles()),
+            is_in_user_mode: AtomicBool::new(false),
+        }
+    }
+
+    /// Charges the CPU time since the last accounting to the thread and its process.
+    ///
+    /// The time is charged as the user CPU time or the kernel CPU time, depending on the mode
+    /// that the thread is running in. This method returns the charged time.
+    pub fn charge(&self, posix_thread: &PosixThread, process: &Process) -> Duration {
+        let now = read_tsc();
+        let irq_now = current_irq_cycles();
+        let last = self.last_cycles.swap(now, Ordering::Relaxed);
+        let irq_last = self.last_irq_cycles.swap(irq_now, Ordering::Relaxed);
+        let cycles = now
+            .saturating_sub(last)
+            .saturating_sub(irq_now.saturating_sub(irq_last));
+        let time = cycles_to_duration(cycles);
+
+        let is_in_user_mode = self.is_in_user_mode.load(Ordering::Relaxed);
+        if is_in_user_mode {
+            posix_thread.prof_clock().user_clock().add_time(time);
+            process.prof_clock().user_clock().add_time(time);
+        } else {
+            posix_thread.prof_clock().kernel_clock().add_time(time);
+            process.prof_clock().kernel_clock().add_time(time);
+        }
+        charge_cpu(cycles, is_in_user_mode);
+
+        time
+    }
+
+    /// Charges the kernel CPU time and marks that the thread enters the user mode.
+    pub fn enter_user_mode(&self, posix_thread: &PosixThread, process: &Process) {
+        self.charge(posix_thread, process);
+        self.is_in_user_mode.store(true, Ordering::Relaxed);
+    }
+
+    /// Charges the user CPU time and marks that the thread leaves the user mode.
+    pub fn leave_user_mode(&self, posix_thread: &PosixThread, process: &Process) {
+        self.charge(posix_thread, process);
+        self.is_in_user_mode.store(false, Ordering::Relaxed);
+    }
+
+    /// Restarts the accounting without charging the time since the last accounting.
+    ///
+    /// This is used when the thread is scheduled on a CPU, because the thread has not been
+    /// running since it was switched out.
+    fn restart(&self) {
+        self.last_cycles.store(read_tsc(), Ordering::Relaxed);
+        self.last_irq_cycles
+            .store(current_irq_cycles(), Ordering::Relaxed);
+    }
+}
+
+cpu_local! {
+    /// The thread whose CPU time is being accounted on the CPU.
+    static ACCOUNTED_THREAD: SpinLock<Weak<Thread>> = SpinLock::new(Weak::new());
+    /// The TSC cycles that the CPU has spent on running threads in the user mode.
+    static USER_CYCLES: AtomicU64 = AtomicU64::new(0);
+    /// The TSC cycles that the CPU has spent on running threads in the kernel mode.
+    static SYSTEM_CYCLES: AtomicU64 = AtomicU64::new(0);
+    /// The number of context switches on the CPU.
+    static NR_SWITCHES: AtomicU64 = AtomicU64::new(0);
+}
+
+/// Accounts the CPU time when a context switch happens on the current CPU.
+///
+/// The time since the last accounting is charged to the previous thread, and the accounting
+/// of the current thread restarts.
+pub(crate) fn account_cpu_time_at_switch(current_thread: Option<&Arc<Thread>>) {
+    let preempt_guard = disable_preempt();
+    let cpu = preempt_guard.current_cpu();
+    NR_SWITCHES.get_on_cpu(cpu).fetch_add(1, Ordering::Relaxed);
+
+    let mut accounted_thread = ACCOUNTED_THREAD.get_on_cpu(cpu).lock();
+    if let Some(prev_thread) = accounted_thread.upgrade() {
+        let prev_posix_thread = prev_thread.as_posix_thread().unwrap();
+        if let Some(prev_process) = prev_posix_thread.weak_process().upgrade() {
+            prev_posix_thread
+                .cpu_time_account()
+                .charge(prev_posix_thread, &prev_process);
+        }
+    }
+
+    *accounted_thread = Weak::new();
+    let Some(thread) = current_thread else {
+        return;
+    };
+    if let Some(posix_thread) = thread.as_posix_thread() {
+        posix_thread.cpu_time_account().restart();
+        *accounted_thread = Arc::downgrade(thread);
+    }
+}
+
+fn charge_cpu(cycles: u64, is_in_user_mode: bool) {
+    let preempt_guard = disable_preempt();
+    let cpu = preempt_guard.current_cpu();
+    let counter = if is_in_user_mode {
+        USER_CYCLES.get_on_cpu(cpu)
+    } else {
+        SYSTEM_CYCLES.get_on_cpu(cpu)
+    };
+    counter.fetch_add(cycles, Ordering::Relaxed);
+}
+
+/// The CPU time statistics of a CPU.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CpuTimeStats {
+    /// The time spent on running threads in the user mode.
+    pub user: Duration,
+    /// The time spent on running threads in the kernel mode.
+    pub system: Duration,
+    /// The time spent on the interrupt top halves.
+    pub irq: Duration,
+    /// The time spent on the interrupt bottom halves.
+    pub softirq: Duration,
+    /// The number of context switches.
+    pub nr_switches: u64,
+}
+
+/// Returns the CPU time statistics of the CPU.
+pub fn cpu_time_stats(cpu: CpuId) -> CpuTimeStats {
+    CpuTimeStats {
+        user: cycles_to_duration(USER_CYCLES.get_on_cpu(cpu).load(Ordering::Relaxed)),
+        system: cycles_to_duration(SYSTEM_CYCLES.get_on_cpu(cpu).load(Ordering::Relaxed)),
+        irq: cycles_to_duration(hardirq_cycles(cpu)),
+        softirq: cycles_to_duration(softirq_cycles(cpu)),
+        nr_switches: NR_SWITCHES.get_on_cpu(cpu).load(Ordering::Relaxed),
+    }
+}
+
+fn cycles_to_duration(cycles: u64) -> Duration {
+    let nanos = cycles as u128 * 1_000_000_000 / tsc_freq() as u128;
+    Duration::from_nanos(nanos as u64)
+}
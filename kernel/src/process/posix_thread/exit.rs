@@ -111,7 +111,7 @@ fn wake_clear_ctid(thread_local: &ThreadLocal) {
     let _ = current_userspace!()
         .write_val(clear_ctid, &0u32)
         .inspect_err(|err| debug!("exit: cannot clear the child TID: {:?}", err));
-    let _ = futex_wake(clear_ctid, 1, None)
+    let _ = futex_wake(clear_ctid, 1, false)
         .inspect_err(|err| debug!("exit: cannot wake the futex on the child TID: {:?}", err));
 
     thread_local.clear_child_tid().set(0);
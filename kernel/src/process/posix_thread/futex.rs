@@ -9,7 +9,10 @@ use ostd::{
 };
 use spin::Once;
 
-use crate::{prelude::*, process::Pid, time::wait::ManagedTimeout};
+use crate::{
+    current_userspace, prelude::*, process::Pid, time::wait::ManagedTimeout,
+    vm::vmar::SharedMemoryKey,
+};
 
 type FutexBitSet = u32;
 type FutexBucketRef = Arc<Mutex<FutexBucket>>;
@@ -24,7 +27,7 @@ pub fn futex_wait(
     futex_val: i32,
     timeout: Option<ManagedTimeout>,
     ctx: &Context,
-    pid: Option<Pid>,
+    is_private: bool,
 ) -> Result<()> {
     futex_wait_bitset(
         futex_addr as _,
@@ -32,7 +35,7 @@ pub fn futex_wait(
         timeout,
         FUTEX_BITSET_MATCH_ANY,
         ctx,
-        pid,
+        is_private,
     )
 }
 
@@ -43,7 +46,7 @@ pub fn futex_wait_bitset(
     timeout: Option<ManagedTimeout>,
     bitset: FutexBitSet,
     ctx: &Context,
-    pid: Option<Pid>,
+    is_private: bool,
 ) -> Result<()> {
     debug!(
         "futex_wait_bitset addr: {:#x}, val: {}, bitset: {:#x}",
@@ -54,14 +57,14 @@ pub fn futex_wait_bitset(
         return_errno_with_message!(Errno::EINVAL, "at least one bit should be set");
     }
 
-    let futex_key = FutexKey::new(futex_addr, bitset, pid);
+    let futex_key = FutexKey::new(futex_addr, bitset, is_private)?;
     let (futex_item, waiter) = FutexItem::create(futex_key);
 
     let (_, futex_bucket_ref) = get_futex_bucket(futex_key);
     // lock futex bucket ref here to avoid data race
     let mut futex_bucket = futex_bucket_ref.lock();
 
-    if !futex_key.load_val(ctx).is_ok_and(|val| val == futex_val) {
+    if !load_futex_val(futex_addr, ctx).is_ok_and(|val| val == futex_val) {
         return_errno_with_message!(
             Errno::EAGAIN,
             "futex value does not match or load_val failed"
@@ -93,8 +96,8 @@ pub fn futex_wait_bitset(
 }
 
 /// Does futex wake
-pub fn futex_wake(futex_addr: Vaddr, max_count: usize, pid: Option<Pid>) -> Result<usize> {
-    futex_wake_bitset(futex_addr, max_count, FUTEX_BITSET_MATCH_ANY, pid)
+pub fn futex_wake(futex_addr: Vaddr, max_count: usize, is_private: bool) -> Result<usize> {
+    futex_wake_bitset(futex_addr, max_count, FUTEX_BITSET_MATCH_ANY, is_private)
 }
 
 /// Does futex wake with bitset
@@ -102,7 +105,7 @@ pub fn futex_wake_bitset(
     futex_addr: Vaddr,
     max_count: usize,
     bitset: FutexBitSet,
-    pid: Option<Pid>,
+    is_private: bool,
 ) -> Result<usize> {
     debug!(
         "futex_wake_bitset addr: {:#x}, max_count: {}, bitset: {:#x}",
@@ -113,7 +116,7 @@ pub fn futex_wake_bitset(
         return_errno_with_message!(Errno::EINVAL, "at least one bit should be set");
     }
 
-    let futex_key = FutexKey::new(futex_addr, bitset, pid);
+    let futex_key = FutexKey::new(futex_addr, bitset, is_private)?;
     let (_, futex_bucket_ref) = get_futex_bucket(futex_key);
     let mut futex_bucket = futex_bucket_ref.lock();
     let res = futex_bucket.remove_and_wake_items(futex_key, max_count);
@@ -128,14 +131,14 @@ pub fn futex_requeue(
     max_nwakes: usize,
     max_nrequeues: usize,
     futex_new_addr: Vaddr,
-    pid: Option<Pid>,
+    is_private: bool,
 ) -> Result<usize> {
     if futex_new_addr == futex_addr {
-        return futex_wake(futex_addr, max_nwakes, pid);
+        return futex_wake(futex_addr, max_nwakes, is_private);
     }
 
-    let futex_key = FutexKey::new(futex_addr, FUTEX_BITSET_MATCH_ANY, pid);
-    let futex_new_key = FutexKey::new(futex_new_addr, FUTEX_BITSET_MATCH_ANY, pid);
+    let futex_key = FutexKey::new(futex_addr, FUTEX_BITSET_MATCH_ANY, is_private)?;
+    let futex_new_key = FutexKey::new(futex_new_addr, FUTEX_BITSET_MATCH_ANY, is_private)?;
     let (bucket_idx, futex_bucket_ref) = get_futex_bucket(futex_key);
     let (new_bucket_idx, futex_new_bucket_ref) = get_futex_bucket(futex_new_key);
 
@@ -209,12 +212,7 @@ impl FutexBucketVec {
     }
 
     pub fn get_bucket(&self, key: FutexKey) -> (usize, FutexBucketRef) {
-        let index = (self.vec.len() - 1) & {
-            // The addr is the multiples of 4, so we ignore the last 2 bits
-            let addr = key.addr() >> 2;
-            // simple hash
-            addr / self.size()
-        };
+        let index = (self.vec.len() - 1) & key.hash();
         (index, self.vec[index].clone())
     }
 
@@ -351,38 +349,69 @@ impl FutexItem {
     }
 }
 
-// The addr of a futex, it should be used to mark different futex word
+/// Loads the value of the futex word.
+fn load_futex_val(futex_addr: Vaddr, ctx: &Context) -> Result<i32> {
+    // FIXME: how to implement a atomic load?
+    warn!("implement an atomic load");
+    ctx.user_space().read_val(futex_addr)
+}
+
+/// The key of a futex, which is used to mark different futex words.
 #[derive(Debug, Clone, Copy)]
 struct FutexKey {
-    addr: Vaddr,
+    location: FutexLocation,
     bitset: FutexBitSet,
-    /// Specify whether this `FutexKey` is process private or shared. If `pid` is
-    /// None, then this `FutexKey` is shared.
-    pid: Option<Pid>,
+}
+
+/// The location of a futex word.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FutexLocation {
+    /// The futex word is in the private memory of the process at the address.
+    Private(Pid, Vaddr),
+    /// The futex word is in the shared memory, which may be mapped by multiple processes at
+    /// different addresses.
+    Shared(SharedMemoryKey),
 }
 
 impl FutexKey {
-    pub fn new(addr: Vaddr, bitset: FutexBitSet, pid: Option<Pid>) -> Self {
-        Self { addr, bitset, pid }
-    }
+    /// Creates the key of the futex word at `addr` in the current process.
+    ///
+    /// Like Linux, if the futex is not private and the futex word is in a shared mapping, the
+    /// futex is keyed on the shared memory rather than the address. So the futex can be used to
+    /// synchronize the processes that share the memory (e.g., process-shared mutexes in a file
+    /// mapping or a System V shared memory segment).
+    pub fn new(addr: Vaddr, bitset: FutexBitSet, is_private: bool) -> Result<Self> {
+        if addr % size_of::<u32>() != 0 {
+            return_errno_with_message!(Errno::EINVAL, "the futex word is not aligned");
+        }
 
-    pub fn load_val(&self, ctx: &Context) -> Result<i32> {
-        // FIXME: how to implement a atomic load?
-        warn!("implement an atomic load");
-        ctx.user_space().read_val(self.addr)
-    }
+        let shared_key = if is_private {
+            None
+        } else {
+            current_userspace!().root_vmar().shared_memory_key(addr)
+        };
+        let location = match shared_key {
+            Some(shared_key) => FutexLocation::Shared(shared_key),
+            None => FutexLocation::Private(current!().pid(), addr),
+        };
 
-    pub fn addr(&self) -> Vaddr {
-        self.addr
+        Ok(Self { location, bitset })
     }
 
     pub fn bitset(&self) -> FutexBitSet {
         self.bitset
     }
 
+    fn hash(&self) -> usize {
+        // The futex words are 4-byte aligned, so we ignore the last 2 bits.
+        match self.location {
+            FutexLocation::Private(pid, addr) => (addr >> 2) ^ (pid as usize),
+            FutexLocation::Shared(shared_key) => shared_key.hash() >> 2,
+        }
+    }
+
     pub fn match_up(&self, another: &Self) -> bool {
-        // TODO: Use hash value to do match_up
-        self.addr == another.addr && (self.bitset & another.bitset) != 0 && self.pid == another.pid
+        self.location == another.location && (self.bitset & another.bitset) != 0
     }
 }
 
@@ -22,24 +22,32 @@ use crate::{
     fs::{file_table::FileTable, thread_info::ThreadFsInfo},
     prelude::*,
     process::signal::constants::SIGCONT,
+    security::keys::ThreadKeyrings,
     thread::{Thread, Tid},
-    time::{clocks::ProfClock, Timer, TimerManager},
+    time::{clocks::ProfClock, TimerManager},
 };
 
 mod builder;
+mod cpu_time;
 mod exit;
 pub mod futex;
 mod name;
 mod posix_thread_ext;
 mod robust_list;
+mod rseq;
+mod syscall_user_dispatch;
 mod thread_local;
 pub mod thread_table;
 
 pub use builder::PosixThreadBuilder;
+pub(crate) use cpu_time::account_cpu_time_at_switch;
+pub use cpu_time::{cpu_time_stats, CpuTimeAccount, CpuTimeStats};
 pub use exit::{do_exit, do_exit_group};
 pub use name::{ThreadName, MAX_THREAD_NAME_LEN};
 pub use posix_thread_ext::{create_posix_task_from_executable, AsPosixThread};
 pub use robust_list::RobustListHead;
+pub use rseq::{Rseq, RseqArea};
+pub use syscall_user_dispatch::{SyscallUserDispatch, SyscallUserDispatchConfig};
 pub use thread_local::{AsThreadLocal, ThreadLocal};
 
 pub struct PosixThread {
@@ -52,6 +60,8 @@ pub struct PosixThread {
 
     /// Process credentials. At the kernel level, credentials are a per-thread attribute.
     credentials: Credentials,
+    /// The thread keyring, the process keyring, and the session keyring.
+    keyrings: ThreadKeyrings,
 
     // Files
     /// File table
@@ -70,6 +80,8 @@ pub struct PosixThread {
 
     /// A profiling clock measures the user CPU time and kernel CPU time in the thread.
     prof_clock: Arc<ProfClock>,
+    /// The state of the accounting that updates `prof_clock`.
+    cpu_time_account: CpuTimeAccount,
 
     /// A manager that manages timers based on the user CPU time of the current thread.
     virtual_timer_manager: Arc<TimerManager>,
@@ -216,26 +228,26 @@ impl PosixThread {
         &self.prof_clock
     }
 
-    /// Creates a timer based on the profiling CPU clock of the current thread.
-    pub fn create_prof_timer<F>(&self, func: F) -> Arc<Timer>
-    where
-        F: Fn() + Send + Sync + 'static,
-    {
-        self.prof_timer_manager.create_timer(func)
+    /// Returns the state of the CPU time accounting of the current thread.
+    pub fn cpu_time_account(&self) -> &CpuTimeAccount {
+        &self.cpu_time_account
     }
 
-    /// Creates a timer based on the user CPU clock of the current thread.
-    pub fn create_virtual_timer<F>(&self, func: F) -> Arc<Timer>
-    where
-        F: Fn() + Send + Sync + 'static,
-    {
-        self.virtual_timer_manager.create_timer(func)
+    /// Returns the manager of the timers based on the profiling CPU clock of the current thread.
+    pub fn prof_timer_manager(&self) -> &Arc<TimerManager> {
+        &self.prof_timer_manager
     }
 
-    /// Checks the `TimerCallback`s that are managed by the `prof_timer_manager`.
-    /// If any have timed out, call the corresponding callback functions.
+    /// Returns the manager of the timers based on the user CPU clock of the current thread.
+    pub fn virtual_timer_manager(&self) -> &Arc<TimerManager> {
+        &self.virtual_timer_manager
+    }
+
+    /// Checks the `TimerCallback`s that are managed by the `prof_timer_manager` and the
+    /// `virtual_timer_manager`. If any have timed out, call the corresponding callback functions.
     pub fn process_expired_timers(&self) {
         self.prof_timer_manager.process_expired_timers();
+        self.virtual_timer_manager.process_expired_timers();
     }
 
     pub fn dequeue_signal(&self, mask: &SigMask) -> Option<Box<dyn Signal>> {
@@ -271,6 +283,11 @@ impl PosixThread {
         ));
         self.credentials.dup().restrict()
     }
+
+    /// Returns the keyrings of the thread.
+    pub fn keyrings(&self) -> &ThreadKeyrings {
+        &self.keyrings
+    }
 }
 
 static POSIX_TID_ALLOCATOR: AtomicU32 = AtomicU32::new(1);
@@ -4,6 +4,7 @@ use ostd::{cpu::context::UserContext, task::Task, user::UserContextApi};
 
 use super::{builder::PosixThreadBuilder, name::ThreadName, PosixThread};
 use crate::{
+    cpu::LinuxAbi,
     fs::{
         fs_resolver::{FsPath, AT_FDCWD},
         thread_info::ThreadFsInfo,
@@ -48,15 +49,15 @@ pub fn create_posix_task_from_executable(
         let fs_resolver = fs.resolver().read();
         let fs_path = FsPath::new(AT_FDCWD, executable_path)?;
         let elf_file = fs.resolver().read().lookup(&fs_path)?;
-        let program_to_load =
-            ProgramToLoad::build_from_file(elf_file, &fs_resolver, argv, envp, 1)?;
+        let program_to_load = ProgramToLoad::build_from_file(elf_file, &fs_resolver, argv, envp)?;
         process_vm.clear_and_map();
-        program_to_load.load_to_vm(process_vm, &fs_resolver)?
+        program_to_load.load_to_vm(process_vm, &fs_resolver, &credentials.dup().restrict())?
     };
 
     let mut user_ctx = UserContext::default();
     user_ctx.set_instruction_pointer(elf_load_info.entry_point() as _);
     user_ctx.set_stack_pointer(elf_load_info.user_stack_top() as _);
+    user_ctx.set_compat(elf_load_info.is_compat());
     let thread_name = Some(ThreadName::new_from_executable_path(executable_path)?);
     let thread_builder = PosixThreadBuilder::new(tid, Arc::new(user_ctx), credentials)
         .thread_name(thread_name)
@@ -0,0 +1,205 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Restartable sequences (rseq).
+//!
+//! A thread registers an rseq area (i.e., `struct rseq`) by the `rseq` system call. The kernel
+//! keeps the current CPU in the area up to date whenever the thread returns to the user space
+//! after it has been switched out, so that the thread can access the per-CPU data without
+//! atomic instructions. The accesses are done in critical sections (i.e., the restartable
+//! sequences, which are described by `struct rseq_cs`). If the thread is switched out or
+//! interrupted by a signal in a critical section, the critical section is aborted by moving the
+//! instruction pointer to its abort handler before the thread returns to the user space.
+//!
+//! Reference: <https://docs.kernel.org/userspace-api/rseq.html>
+
+use core::cell::Cell;
+
+use ostd::{
+    cpu::{context::UserContext, PinCurrentCpu},
+    task::disable_preempt,
+    user::UserContextApi,
+};
+
+use super::do_exit_group;
+use crate::{
+    prelude::*,
+    process::{signal::constants::SIGSEGV, TermStatus},
+};
+
+/// An rseq area that is registered by a thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RseqArea {
+    /// The address of `struct rseq`.
+    pub addr: Vaddr,
+    /// The length of `struct rseq`.
+    pub len: u32,
+    /// The signature that must precede the abort handlers.
+    pub sig: u32,
+}
+
+/// The rseq state of a thread.
+pub struct Rseq {
+    area: Cell<Option<RseqArea>>,
+    /// Whether the thread has been switched out or interrupted by a signal since it returns
+    /// to the user space last time.
+    is_pending: Cell<bool>,
+}
+
+/// The critical section descriptor, i.e., `struct rseq_cs`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct RseqCs {
+    version: u32,
+    flags: u32,
+    start_ip: u64,
+    post_commit_offset: u64,
+    abort_ip: u64,
+}
+
+// The offsets of the fields in `struct rseq`.
+const CPU_ID_START_OFFSET: usize = 0;
+const CPU_ID_OFFSET: usize = 4;
+const RSEQ_CS_OFFSET: usize = 8;
+const FLAGS_OFFSET: usize = 16;
+const NODE_ID_OFFSET: usize = 20;
+const MM_CID_OFFSET: usize = 24;
+
+/// The value of `cpu_id` when the area is not registered.
+const RSEQ_CPU_ID_UNINITIALIZED: u32 = u32::MAX;
+
+impl Rseq {
+    pub(super) fn new(area: Option<RseqArea>) -> Self {
+        Self {
+            area: Cell::new(area),
+            is_pending: Cell::new(area.is_some()),
+        }
+    }
+
+    /// Returns the registered area.
+    pub fn area(&self) -> Option<RseqArea> {
+        self.area.get()
+    }
+
+    /// Registers the area, whose CPU fields are updated before the thread returns to the
+    /// user space.
+    pub fn register(&self, area: RseqArea) {
+        self.area.set(Some(area));
+        self.is_pending.set(true);
+    }
+
+    /// Unregisters the area, and resets its CPU fields.
+    pub fn unregister(&self, ctx: &Context) -> Result<()> {
+        let Some(area) = self.area.take() else {
+            return Ok(());
+        };
+        write_cpu_fields(ctx, &area, 0, RSEQ_CPU_ID_UNINITIALIZED)
+    }
+
+    /// Forgets the area without accessing it, which is called when the address space of the
+    /// thread is replaced.
+    pub fn clear(&self) {
+        self.area.set(None);
+    }
+
+    /// Marks that the thread has been switched out.
+    pub fn notify_preempted(&self) {
+        if self.area.get().is_some() {
+            self.is_pending.set(true);
+        }
+    }
+
+    /// Aborts the critical section and updates the area before the thread returns to the user
+    /// space, if the thread has been switched out or interrupted by a signal.
+    ///
+    /// The current process is killed by `SIGSEGV` if the area or the critical section is
+    /// invalid, as Linux does.
+    pub fn handle_return_to_user(&self, ctx: &Context, user_ctx: &mut UserContext) {
+        // The pending flag is cleared before the CPU is read. If the thread is switched out
+        // while the area is being updated, the flag is set again and the update is redone,
+        // so the area never reports a stale CPU.
+        while self.is_pending.replace(false) {
+            let Some(area) = self.area.get() else {
+                return;
+            };
+
+            let cpu = disable_preempt().current_cpu().as_usize() as u32;
+            let res =
+                fixup_ip(ctx, &area, user_ctx).and_then(|_| write_cpu_fields(ctx, &area, cpu, cpu));
+            if let Err(err) = res {
+                debug!("failed to handle the rseq area: {:?}", err);
+                do_exit_group(TermStatus::Killed(SIGSEGV));
+                return;
+            }
+        }
+    }
+
+    /// Aborts the critical section before a signal handler runs, so that the thread returns
+    /// to the abort handler after the signal handler returns.
+    pub fn handle_signal(&self, ctx: &Context, user_ctx: &mut UserContext) -> Result<()> {
+        let Some(area) = self.area.get() else {
+            return Ok(());
+        };
+        self.is_pending.set(true);
+        fixup_ip(ctx, &area, user_ctx)
+    }
+}
+
+/// Moves the instruction pointer to the abort handler if it is in the critical section.
+fn fixup_ip(ctx: &Context, area: &RseqArea, user_ctx: &mut UserContext) -> Result<()> {
+    let user_space = ctx.user_space();
+
+    if user_space.read_val::<u32>(area.addr + FLAGS_OFFSET)? != 0 {
+        return_errno_with_message!(Errno::EINVAL, "the rseq flags are deprecated");
+    }
+
+    let cs_addr = user_space.read_val::<u64>(area.addr + RSEQ_CS_OFFSET)? as Vaddr;
+    if cs_addr == 0 {
+        return Ok(());
+    }
+    let cs = user_space.read_val::<RseqCs>(cs_addr)?;
+
+    let Some(end_ip) = cs.start_ip.checked_add(cs.post_commit_offset) else {
+        return_errno_with_message!(Errno::EINVAL, "the critical section overflows");
+    };
+    let ip_range = cs.start_ip..end_ip;
+    if cs.version != 0 || cs.flags != 0 || ip_range.contains(&cs.abort_ip) {
+        return_errno_with_message!(Errno::EINVAL, "the critical section is invalid");
+    }
+    // The abort handler must be preceded by the signature, which prevents the instruction
+    // pointer from being moved to arbitrary code.
+    let sig_addr = (cs.abort_ip as Vaddr)
+        .checked_sub(size_of::<u32>())
+        .ok_or(Errno::EINVAL)?;
+    if user_space.read_val::<u32>(sig_addr)? != area.sig {
+        return_errno_with_message!(
+            Errno::EINVAL,
+            "the signature of the abort handler mismatches"
+        );
+    }
+
+    // The descriptor is cleared whenever it is checked, as Linux does, so that the critical
+    // sections that have been completed are not checked again.
+    user_space.write_val(area.addr + RSEQ_CS_OFFSET, &0u64)?;
+    if ip_range.contains(&(user_ctx.instruction_pointer() as u64)) {
+        user_ctx.set_instruction_pointer(cs.abort_ip as usize);
+    }
+    Ok(())
+}
+
+/// Writes the CPU fields of the area.
+fn write_cpu_fields(ctx: &Context, area: &RseqArea, cpu_id_start: u32, cpu_id: u32) -> Result<()> {
+    let user_space = ctx.user_space();
+
+    user_space.write_val(area.addr + CPU_ID_START_OFFSET, &cpu_id_start)?;
+    user_space.write_val(area.addr + CPU_ID_OFFSET, &cpu_id)?;
+    // NUMA is not supported, so the node is always zero.
+    user_space.write_val(area.addr + NODE_ID_OFFSET, &0u32)?;
+    // The CPU is used as the concurrency ID, which is unique among the running threads.
+    let mm_cid = if cpu_id == RSEQ_CPU_ID_UNINITIALIZED {
+        0
+    } else {
+        cpu_id
+    };
+    user_space.write_val(area.addr + MM_CID_OFFSET, &mm_cid)?;
+    Ok(())
+}
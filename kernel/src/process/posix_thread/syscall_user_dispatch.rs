@@ -0,0 +1,119 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Syscall user dispatch, which redirects the system calls back to the user space.
+//!
+//! A thread enables it by `prctl(PR_SET_SYSCALL_USER_DISPATCH)` with a region of code and an
+//! optional selector byte. The system calls that are made outside of the region are not made if
+//! the selector is `SYSCALL_DISPATCH_FILTER_BLOCK` (or if there is no selector). Instead, the
+//! thread receives a `SIGSYS` with `SYS_USER_DISPATCH`, whose handler can emulate the system
+//! call. The registers are left untouched, so the handler sees the system call number and the
+//! arguments. This is used by compatibility layers (e.g., Wine) to emulate the system calls of
+//! another operating system without rewriting its code.
+//!
+//! There is no `rt_sigreturn` trampoline in the vDSO, so the signal restorer must be in the
+//! region, or the selector must be set to `SYSCALL_DISPATCH_FILTER_ALLOW` before the signal
+//! handler returns.
+//!
+//! The setting is per thread, and is not inherited by the children or preserved across
+//! `execve`.
+//!
+//! Reference: <https://docs.kernel.org/admin-guide/syscall-user-dispatch.html>
+
+use core::cell::Cell;
+
+use ostd::{cpu::context::UserContext, user::UserContextApi};
+
+use super::do_exit_group;
+use crate::{
+    cpu::LinuxAbi,
+    prelude::*,
+    process::{
+        signal::{
+            constants::{SIGSEGV, SIGSYS, SYS_USER_DISPATCH},
+            signals::syscall::SyscallSignal,
+        },
+        TermStatus,
+    },
+    security::seccomp::audit_arch,
+};
+
+/// The value of the selector that allows the system calls.
+const SYSCALL_DISPATCH_FILTER_ALLOW: u8 = 0;
+/// The value of the selector that dispatches the system calls to the user space.
+const SYSCALL_DISPATCH_FILTER_BLOCK: u8 = 1;
+
+/// The configuration of an enabled syscall user dispatch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyscallUserDispatchConfig {
+    /// The start of the region where the system calls are always allowed.
+    pub offset: Vaddr,
+    /// The length of the region.
+    pub len: usize,
+    /// The address of the selector byte, or `None` to dispatch all the system calls outside of
+    /// the region.
+    pub selector: Option<Vaddr>,
+}
+
+/// The syscall user dispatch state of a thread.
+pub struct SyscallUserDispatch {
+    config: Cell<Option<SyscallUserDispatchConfig>>,
+}
+
+impl SyscallUserDispatch {
+    pub(super) fn new() -> Self {
+        Self {
+            config: Cell::new(None),
+        }
+    }
+
+    /// Enables syscall user dispatch, or updates its configuration.
+    pub fn enable(&self, config: SyscallUserDispatchConfig) {
+        self.config.set(Some(config));
+    }
+
+    /// Disables syscall user dispatch.
+    pub fn disable(&self) {
+        self.config.set(None);
+    }
+
+    /// Checks whether the system call should be dispatched to the user space.
+    ///
+    /// It returns `true` if the system call should not be made, in which case a `SIGSYS` is
+    /// queued, or the current process is killed if the selector is invalid.
+    pub fn check_syscall(&self, ctx: &Context, user_ctx: &UserContext, is_compat: bool) -> bool {
+        let Some(config) = self.config.get() else {
+            return false;
+        };
+
+        // The instruction pointer is after the instruction that makes the system call.
+        let ip = user_ctx.instruction_pointer();
+        if ip.wrapping_sub(config.offset) < config.len {
+            return false;
+        }
+
+        if let Some(selector) = config.selector {
+            match ctx.user_space().read_val::<u8>(selector) {
+                Ok(SYSCALL_DISPATCH_FILTER_ALLOW) => return false,
+                Ok(SYSCALL_DISPATCH_FILTER_BLOCK) => (),
+                Ok(_) => {
+                    do_exit_group(TermStatus::Killed(SIGSYS));
+                    return true;
+                }
+                Err(_) => {
+                    do_exit_group(TermStatus::Killed(SIGSEGV));
+                    return true;
+                }
+            }
+        }
+
+        let signal = SyscallSignal::new(
+            SYS_USER_DISPATCH,
+            0,
+            ip,
+            user_ctx.syscall_num() as i32,
+            audit_arch(is_compat),
+        );
+        ctx.posix_thread.enqueue_signal(Box::new(signal));
+        true
+    }
+}
@@ -5,7 +5,7 @@ use core::cell::{Cell, RefCell};
 use aster_rights::Full;
 use ostd::{mm::Vaddr, sync::RwArc, task::CurrentTask};
 
-use super::RobustListHead;
+use super::{RobustListHead, Rseq, RseqArea, SyscallUserDispatch};
 use crate::{fs::file_table::FileTable, process::signal::SigStack, vm::vmar::Vmar};
 
 /// Local data for a POSIX thread.
@@ -22,6 +22,14 @@ pub struct ThreadLocal {
     // https://man7.org/linux/man-pages/man2/get_robust_list.2.html
     robust_list: RefCell<Option<RobustListHead>>,
 
+    // Restartable sequences.
+    // https://docs.kernel.org/userspace-api/rseq.html
+    rseq: Rseq,
+
+    // Syscall user dispatch.
+    // https://docs.kernel.org/admin-guide/syscall-user-dispatch.html
+    syscall_user_dispatch: SyscallUserDispatch,
+
     // Files.
     file_table: RefCell<RwArc<FileTable>>,
 
@@ -40,12 +48,15 @@ impl ThreadLocal {
         clear_child_tid: Vaddr,
         root_vmar: Option<Vmar<Full>>,
         file_table: RwArc<FileTable>,
+        rseq_area: Option<RseqArea>,
     ) -> Self {
         Self {
             set_child_tid: Cell::new(set_child_tid),
             clear_child_tid: Cell::new(clear_child_tid),
             root_vmar: RefCell::new(root_vmar),
             robust_list: RefCell::new(None),
+            rseq: Rseq::new(rseq_area),
+            syscall_user_dispatch: SyscallUserDispatch::new(),
             file_table: RefCell::new(file_table),
             sig_context: Cell::new(None),
             sig_stack: RefCell::new(None),
@@ -68,6 +79,14 @@ impl ThreadLocal {
         &self.robust_list
     }
 
+    pub fn rseq(&self) -> &Rseq {
+        &self.rseq
+    }
+
+    pub fn syscall_user_dispatch(&self) -> &SyscallUserDispatch {
+        &self.syscall_user_dispatch
+    }
+
     pub fn file_table(&self) -> &RefCell<RwArc<FileTable>> {
         &self.file_table
     }
@@ -1,14 +1,15 @@
 // SPDX-License-Identifier: MPL-2.0
 
-#![expect(unused_variables)]
-
-use ostd::sync::WaitQueue;
+use core::sync::atomic::Ordering;
 
 use crate::{
     prelude::*,
     process::{
+        posix_thread::AsPosixThread,
         signal::{
-            constants::{SIGCONT, SIGHUP},
+            constants::{SIGCONT, SIGHUP, SIGTTIN, SIGTTOU},
+            sig_action::SigAction,
+            sig_num::SigNum,
             signals::kernel::KernelSignal,
         },
         ProcessGroup, Session,
@@ -23,7 +24,6 @@ use crate::{
 pub struct JobControl {
     foreground: SpinLock<Weak<ProcessGroup>>,
     session: SpinLock<Weak<Session>>,
-    wait_queue: WaitQueue,
 }
 
 impl JobControl {
@@ -32,7 +32,6 @@ impl JobControl {
         Self {
             foreground: SpinLock::new(Weak::new()),
             session: SpinLock::new(Weak::new()),
-            wait_queue: WaitQueue::new(),
         }
     }
 
@@ -74,24 +73,26 @@ impl JobControl {
         let session = current.session().unwrap();
         *self.session.lock() = Arc::downgrade(&session);
 
-        self.wait_queue.wake_all();
         Ok(())
     }
 
     /// Releases the current session from this terminal.
     pub fn release_current_session(&self) -> Result<()> {
-        let Some(session) = self.session() else {
+        if self.session().is_none() {
             return_errno_with_message!(
                 Errno::ENOTTY,
                 "the terminal is not controlling terminal now"
             );
-        };
+        }
 
         if let Some(foreground) = self.foreground() {
             foreground.broadcast_signal(KernelSignal::new(SIGHUP));
             foreground.broadcast_signal(KernelSignal::new(SIGCONT));
         }
 
+        *self.foreground.lock() = Weak::new();
+        *self.session.lock() = Weak::new();
+
         Ok(())
     }
 
@@ -130,38 +131,84 @@ impl JobControl {
         }
 
         *self.foreground.lock() = Arc::downgrade(process_group);
-        self.wait_queue.wake_all();
         Ok(())
     }
 
-    /// Wait until the current process is the foreground process group. If
-    /// the foreground process group is None, returns true.
+    // *************** Background access ***************
+
+    /// Checks whether the current process can read from the terminal.
+    ///
+    /// If the current process is in a background process group of the session controlled by
+    /// the terminal, `SIGTTIN` is sent to the process group and `ERESTARTSYS` is returned. If
+    /// the signal is ignored or blocked, or the process group is orphaned, `EIO` is returned.
     ///
     /// # Panics
     ///
     /// This function should only be called in process context.
-    pub fn wait_until_in_foreground(&self) -> Result<()> {
-        // Fast path
-        if self.current_belongs_to_foreground() {
-            return Ok(());
-        }
+    pub fn check_background_read(&self) -> Result<()> {
+        self.check_background_access(SIGTTIN)
+    }
 
-        // Slow path
-        self.wait_queue.pause_until(|| {
-            if self.current_belongs_to_foreground() {
-                Some(())
-            } else {
-                None
-            }
-        })
+    /// Checks whether the current process can write to the terminal or change its settings.
+    ///
+    /// This is similar to [`Self::check_background_read`], except that `SIGTTOU` is sent,
+    /// and the access is allowed if the signal is ignored or blocked.
+    ///
+    /// # Panics
+    ///
+    /// This function should only be called in process context.
+    pub fn check_background_write(&self) -> Result<()> {
+        self.check_background_access(SIGTTOU)
     }
 
-    fn current_belongs_to_foreground(&self) -> bool {
+    fn check_background_access(&self, sig_num: SigNum) -> Result<()> {
+        let Some(session) = self.session() else {
+            return Ok(());
+        };
         let Some(foreground) = self.foreground() else {
-            return true;
+            return Ok(());
         };
 
-        foreground.contains_process(current!().pid())
+        let current = current!();
+        let Some(process_group) = current.process_group() else {
+            return Ok(());
+        };
+        if Arc::ptr_eq(&process_group, &foreground)
+            || !process_group
+                .session()
+                .is_some_and(|group_session| Arc::ptr_eq(&group_session, &session))
+        {
+            return Ok(());
+        }
+
+        let is_ignored = {
+            let current_thread = current_thread!();
+            let posix_thread = current_thread.as_posix_thread().unwrap();
+            posix_thread.sig_mask().contains(sig_num, Ordering::Relaxed)
+                || matches!(
+                    current.sig_dispositions().lock().get(sig_num),
+                    SigAction::Ign
+                )
+        };
+        if is_ignored {
+            if sig_num == SIGTTOU {
+                return Ok(());
+            }
+            return_errno_with_message!(Errno::EIO, "the background read is not allowed");
+        }
+
+        if process_group.is_orphaned() {
+            return_errno_with_message!(
+                Errno::EIO,
+                "the orphaned process group cannot access the terminal"
+            );
+        }
+
+        process_group.broadcast_signal(KernelSignal::new(sig_num));
+        return_errno_with_message!(
+            Errno::ERESTARTSYS,
+            "the background process group is stopped"
+        );
     }
 }
 
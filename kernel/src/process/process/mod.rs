@@ -1,9 +1,13 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use core::{
+    sync::atomic::{AtomicBool, AtomicU32, Ordering},
+    time::Duration,
+};
 
 use self::timer_manager::PosixTimerManager;
 use super::{
+    cgroup::Cgroup,
     posix_thread::{allocate_posix_tid, AsPosixThread},
     process_table,
     process_vm::{Heap, InitStackReader, ProcessVm, ProcessVmarGuard},
@@ -22,11 +26,12 @@ use crate::{
     prelude::*,
     sched::{AtomicNice, Nice},
     thread::{AsThread, Thread},
-    time::clocks::ProfClock,
+    time::clocks::{BootTimeClock, ProfClock},
 };
 
 mod builder;
 mod job_control;
+mod posix_timer;
 mod process_group;
 mod session;
 mod terminal;
@@ -36,6 +41,7 @@ use atomic_integer_wrapper::define_atomic_version_of_integer_like_type;
 pub use builder::ProcessBuilder;
 pub use job_control::JobControl;
 use ostd::{sync::WaitQueue, task::Task};
+pub use posix_timer::{PosixTimer, TimerNotify};
 pub use process_group::ProcessGroup;
 pub use session::Session;
 pub use terminal::Terminal;
@@ -85,6 +91,8 @@ pub struct Process {
     /// According to POSIX.1, the nice value is a per-process attribute,
     /// the threads in a process should share a nice value.
     nice: AtomicNice,
+    /// The cgroup that the process belongs to.
+    cgroup: Mutex<Arc<Cgroup>>,
 
     // Child reaper attribute
     /// Whether the process is a child subreaper.
@@ -112,9 +120,17 @@ pub struct Process {
 
     /// A profiling clock measures the user CPU time and kernel CPU time of the current process.
     prof_clock: Arc<ProfClock>,
+    /// A profiling clock measures the CPU time of the children that have been reaped,
+    /// including the CPU time of their reaped descendants.
+    children_prof_clock: Arc<ProfClock>,
+    /// The time when the process was created, measured since the system boot.
+    start_time: Duration,
 
     /// A manager that manages timer resources and utilities of the process.
     timer_manager: PosixTimerManager,
+
+    /// Whether the system calls of the process are logged (see `/proc/[pid]/strace`).
+    is_syscall_traced: AtomicBool,
 }
 
 /// Representing a parent process by holding a weak reference to it and its PID.
@@ -200,6 +216,10 @@ impl Process {
 
         let prof_clock = ProfClock::new();
 
+        let cgroup = parent
+            .upgrade()
+            .map_or_else(|| Cgroup::root().clone(), |parent| parent.cgroup());
+
         Arc::new_cyclic(|process_ref: &Weak<Process>| Self {
             pid,
             tasks: Mutex::new(TaskSet::new()),
@@ -217,8 +237,12 @@ impl Process {
             exit_signal: AtomicSigNum::new_empty(),
             resource_limits,
             nice: AtomicNice::new(nice),
+            cgroup: Mutex::new(cgroup),
             timer_manager: PosixTimerManager::new(&prof_clock, process_ref),
             prof_clock,
+            children_prof_clock: ProfClock::new(),
+            start_time: BootTimeClock::get().read_time(),
+            is_syscall_traced: AtomicBool::new(false),
         })
     }
 
@@ -301,6 +325,16 @@ impl Process {
         &self.prof_clock
     }
 
+    /// Gets the profiling clock of the reaped children of the process.
+    pub fn children_prof_clock(&self) -> &Arc<ProfClock> {
+        &self.children_prof_clock
+    }
+
+    /// Returns the time when the process was created, measured since the system boot.
+    pub fn start_time(&self) -> Duration {
+        self.start_time
+    }
+
     /// Gets the timer resources and utilities of the process.
     pub fn timer_manager(&self) -> &PosixTimerManager {
         &self.timer_manager
@@ -330,6 +364,15 @@ impl Process {
         self.tasks.lock().main().as_thread().unwrap().clone()
     }
 
+    /// Returns the cgroup that the process belongs to.
+    pub fn cgroup(&self) -> Arc<Cgroup> {
+        self.cgroup.lock().clone()
+    }
+
+    pub(in crate::process) fn set_cgroup(&self, cgroup: Arc<Cgroup>) {
+        *self.cgroup.lock() = cgroup;
+    }
+
     // *********** Parent and child ***********
     pub fn parent(&self) -> &ParentProcess {
         &self.parent
@@ -627,7 +670,7 @@ impl Process {
     /// chooses an arbitrary thread to which to deliver the signal.
     ///
     /// TODO: restrict these method with access control tool.
-    pub fn enqueue_signal(&self, signal: impl Signal + Clone + 'static) {
+    pub fn enqueue_signal(&self, signal: impl Signal + 'static) {
         if self.status.is_zombie() {
             return;
         }
@@ -724,6 +767,20 @@ impl Process {
             }
         }
     }
+
+    // ******************* Syscall tracing ********************
+
+    /// Sets whether the system calls of the process are logged.
+    ///
+    /// The attribute is inherited by the child processes that are created afterwards.
+    pub fn set_syscall_traced(&self, is_traced: bool) {
+        self.is_syscall_traced.store(is_traced, Ordering::Relaxed);
+    }
+
+    /// Returns whether the system calls of the process are logged.
+    pub fn is_syscall_traced(&self) -> bool {
+        self.is_syscall_traced.load(Ordering::Relaxed)
+    }
 }
 
 #[cfg(ktest)]
@@ -0,0 +1,159 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use super::Process;
+use crate::{
+    prelude::*,
+    process::{
+        posix_thread::AsPosixThread,
+        signal::{c_types::sigval_t, sig_num::SigNum, signals::timer::TimerSignal},
+    },
+    thread::{
+        work_queue::{submit_work_item, work_item::WorkItem, WorkPriority},
+        Thread,
+    },
+    time::{Timer, TimerManager},
+};
+
+/// How the expirations of a POSIX timer are notified.
+pub enum TimerNotify {
+    /// Nothing is notified.
+    None,
+    /// A signal is sent to the process.
+    Process(Weak<Process>, SigNum, sigval_t),
+    /// A signal is sent to the thread.
+    Thread(Weak<Thread>, SigNum, sigval_t),
+}
+
+/// A POSIX timer created by `timer_create`.
+///
+/// Like Linux, at most one signal of a POSIX timer can be queued at the same time. If the
+/// timer expires while its signal is still queued, the expiration is counted as an overrun
+/// instead of generating another signal.
+pub struct PosixTimer {
+    id: usize,
+    timer: Arc<Timer>,
+    notify: TimerNotify,
+    /// The work item that sends the signal, since signals cannot be enqueued in the interrupt
+    /// context.
+    send_signal_work: Arc<WorkItem>,
+    /// Whether the signal of the timer has been queued and has not been delivered.
+    is_signal_pending: AtomicBool,
+    /// The number of the overruns since the queued signal is generated.
+    overrun: AtomicU32,
+    /// The number of the overruns of the last delivered signal.
+    last_overrun: AtomicU32,
+}
+
+impl PosixTimer {
+    /// Creates a POSIX timer managed by `timer_manager`.
+    pub(super) fn new(
+        id: usize,
+        timer_manager: &Arc<TimerManager>,
+        notify: TimerNotify,
+    ) -> Arc<Self> {
+        Arc::new_cyclic(|weak_self: &Weak<Self>| {
+            let weak_timer = weak_self.clone();
+            let timer = timer_manager.create_timer(move || {
+                if let Some(posix_timer) = weak_timer.upgrade() {
+                    posix_timer.on_expire();
+                }
+            });
+
+            let weak_timer = weak_self.clone();
+            let send_signal_work = WorkItem::new(Box::new(move || {
+                if let Some(posix_timer) = weak_timer.upgrade() {
+                    posix_timer.send_signal();
+                }
+            }));
+
+            Self {
+                id,
+                timer,
+                notify,
+                send_signal_work,
+                is_signal_pending: AtomicBool::new(false),
+                overrun: AtomicU32::new(0),
+                last_overrun: AtomicU32::new(0),
+            }
+        })
+    }
+
+    /// Returns the ID of the timer.
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Returns the underlying timer.
+    pub fn timer(&self) -> &Arc<Timer> {
+        &self.timer
+    }
+
+    /// Returns the number of the overruns of the last delivered signal.
+    pub fn overrun(&self) -> u32 {
+        self.last_overrun.load(Ordering::Relaxed)
+    }
+
+    fn on_expire(&self) {
+        if matches!(self.notify, TimerNotify::None) {
+            return;
+        }
+
+        let missed = self.timer.missed_expirations().min(u32::MAX as u64) as u32;
+        if self.is_signal_pending.swap(true, Ordering::AcqRel) {
+            self.add_overrun(missed.saturating_add(1));
+            return;
+        }
+
+        self.add_overrun(missed);
+        submit_work_item(self.send_signal_work.clone(), WorkPriority::High);
+    }
+
+    fn send_signal(self: Arc<Self>) {
+        match &self.notify {
+            TimerNotify::None => (),
+            TimerNotify::Process(process, sig_num, value) => {
+                let (sig_num, value) = (*sig_num, *value);
+                if let Some(process) = process.upgrade() {
+                    process.enqueue_signal(TimerSignal::new(sig_num, value, self));
+                    return;
+                }
+            }
+            TimerNotify::Thread(thread, sig_num, value) => {
+                let (sig_num, value) = (*sig_num, *value);
+                if let Some(thread) = thread.upgrade() {
+                    let posix_thread = thread.as_posix_thread().unwrap();
+                    posix_thread.enqueue_signal(Box::new(TimerSignal::new(sig_num, value, self)));
+                    return;
+                }
+            }
+        }
+
+        // The signal is not queued because the target no longer exists.
+        self.is_signal_pending.store(false, Ordering::Release);
+    }
+
+    fn add_overrun(&self, nr_overruns: u32) {
+        // Like Linux, the overrun count saturates at `DELAYTIMER_MAX`, which is `i32::MAX`.
+        let _ = self
+            .overrun
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |overrun| {
+                Some(overrun.saturating_add(nr_overruns).min(i32::MAX as u32))
+            });
+    }
+
+    /// Collects the overruns when the signal of the timer is delivered.
+    ///
+    /// The returned value is reported as the overrun count of the signal.
+    pub(in crate::process) fn collect_overrun(&self) -> u32 {
+        let overrun = self.overrun.swap(0, Ordering::Relaxed);
+        self.last_overrun.store(overrun, Ordering::Relaxed);
+        overrun
+    }
+
+    /// Marks that the signal of the timer is no longer queued.
+    pub(in crate::process) fn clear_signal_pending(&self) {
+        self.is_signal_pending.store(false, Ordering::Release);
+    }
+}
@@ -97,6 +97,35 @@ impl ProcessGroup {
     pub fn session(&self) -> Option<Arc<Session>> {
         self.inner.lock().session.upgrade()
     }
+
+    /// Returns whether the process group is orphaned.
+    ///
+    /// A process group is orphaned if the parent of every member is either a member of the
+    /// group itself or not a member of the group's session.
+    pub fn is_orphaned(&self) -> bool {
+        let (processes, session) = {
+            let inner = self.inner.lock();
+            let processes: Vec<_> = inner.processes.values().cloned().collect();
+            (processes, inner.session.upgrade())
+        };
+        let Some(session) = session else {
+            return true;
+        };
+
+        processes.iter().all(|process| {
+            let Some(parent) = process.parent().lock().process().upgrade() else {
+                return true;
+            };
+            let Some(parent_group) = parent.process_group() else {
+                return true;
+            };
+
+            parent_group.pgid() == self.pgid
+                || !parent_group
+                    .session()
+                    .is_some_and(|parent_session| Arc::ptr_eq(&parent_session, &session))
+        })
+    }
 }
 
 /// A scoped lock for a process group.
@@ -8,17 +8,18 @@ use alloc::{
 use core::time::Duration;
 
 use id_alloc::IdAlloc;
-use ostd::{
-    arch::{timer::TIMER_FREQ, trap::is_kernel_interrupted},
-    sync::Mutex,
-    timer,
-};
+use ostd::{arch::trap::is_kernel_interrupted, sync::Mutex, timer};
 
-use super::Process;
+use super::{PosixTimer, Process, TimerNotify};
 use crate::{
+    prelude::*,
     process::{
         posix_thread::AsPosixThread,
-        signal::{constants::SIGALRM, signals::kernel::KernelSignal},
+        signal::{
+            constants::{SIGALRM, SIGKILL, SIGXCPU},
+            signals::kernel::KernelSignal,
+        },
+        ResourceType::RLIMIT_CPU,
     },
     thread::{
         work_queue::{submit_work_item, work_item::WorkItem},
@@ -26,7 +27,7 @@ use crate::{
     },
     time::{
         clocks::{ProfClock, RealTimeClock},
-        Timer, TimerManager,
+        Clock, Timer, TimerManager,
     },
 };
 
@@ -48,25 +49,12 @@ fn update_cpu_time() {
         return;
     };
     let timer_manager = process.timer_manager();
-    let jiffies_interval = Duration::from_millis(1000 / TIMER_FREQ);
-    // Based on whether the timer interrupt occurs in kernel mode or user mode,
-    // the function will add the duration of one timer interrupt interval to the
-    // corresponding CPU clocks.
-    if is_kernel_interrupted() {
-        posix_thread
-            .prof_clock()
-            .kernel_clock()
-            .add_time(jiffies_interval);
-        process
-            .prof_clock()
-            .kernel_clock()
-            .add_time(jiffies_interval);
-    } else {
-        posix_thread
-            .prof_clock()
-            .user_clock()
-            .add_time(jiffies_interval);
-        process.prof_clock().user_clock().add_time(jiffies_interval);
+    // Charge the CPU time since the last accounting, which is more precise than adding the
+    // duration of one timer interrupt interval.
+    let charged_time = posix_thread
+        .cpu_time_account()
+        .charge(posix_thread, &process);
+    if !is_kernel_interrupted() {
         timer_manager
             .virtual_timer()
             .timer_manager()
@@ -77,6 +65,42 @@ fn update_cpu_time() {
         .timer_manager()
         .process_expired_timers();
     posix_thread.process_expired_timers();
+
+    check_cpu_time_limit(&process, charged_time);
+}
+
+/// Sends a signal to the process if its CPU time exceeds `RLIMIT_CPU`.
+///
+/// Like Linux, `SIGXCPU` is sent every second after the soft limit is reached,
+/// and `SIGKILL` is sent once the hard limit is reached.
+fn check_cpu_time_limit(process: &Arc<Process>, charged_time: Duration) {
+    let cpu_time = process.prof_clock().read_time();
+    let cpu_secs = cpu_time.as_secs();
+    // Only check the limit when a new second of CPU time begins.
+    if cpu_time.saturating_sub(charged_time).as_secs() == cpu_secs {
+        return;
+    }
+
+    let rlimit_cpu = process.resource_limits().get_rlimit(RLIMIT_CPU);
+    let signum = if cpu_secs >= rlimit_cpu.get_max() {
+        SIGKILL
+    } else if cpu_secs >= rlimit_cpu.get_cur() {
+        SIGXCPU
+    } else {
+        return;
+    };
+
+    // Signals cannot be enqueued in the interrupt context, so they are sent in a work item.
+    let process_ref = Arc::downgrade(process);
+    let send_signal = move || {
+        if let Some(process) = process_ref.upgrade() {
+            process.enqueue_signal(KernelSignal::new(signum));
+        }
+    };
+    submit_work_item(
+        WorkItem::new(Box::new(send_signal)),
+        crate::thread::work_queue::WorkPriority::High,
+    );
 }
 
 /// Registers a function to update the CPU clock in processes and
@@ -97,7 +121,7 @@ pub struct PosixTimerManager {
     id_allocator: Mutex<IdAlloc>,
     /// A container managing all POSIX timers created by `timer_create()` syscall
     /// within the process context.
-    posix_timers: Mutex<Vec<Option<Arc<Timer>>>>,
+    posix_timers: Mutex<Vec<Option<Arc<PosixTimer>>>>,
 }
 
 fn create_process_timer_callback(process_ref: &Weak<Process>) -> impl Fn() + Clone {
@@ -156,39 +180,39 @@ impl PosixTimerManager {
         &self.prof_timer
     }
 
-    /// Creates a timer based on the profiling CPU clock of the current process.
-    pub fn create_prof_timer<F>(&self, func: F) -> Arc<Timer>
-    where
-        F: Fn() + Send + Sync + 'static,
-    {
-        self.prof_timer.timer_manager().create_timer(func)
-    }
-
-    /// Creates a timer based on the user CPU clock of the current process.
-    pub fn create_virtual_timer<F>(&self, func: F) -> Arc<Timer>
+    /// Creates a POSIX timer managed by `timer_manager`, and allocates a timer ID for this timer.
+    /// Returns the timer ID.
+    ///
+    /// The way to notify the expirations is created by `new_notify` with the timer ID, since the
+    /// timer ID is used as the signal value by default.
+    pub fn create_posix_timer<F>(
+        &self,
+        timer_manager: &Arc<TimerManager>,
+        new_notify: F,
+    ) -> Result<usize>
     where
-        F: Fn() + Send + Sync + 'static,
+        F: FnOnce(usize) -> TimerNotify,
     {
-        self.virtual_timer.timer_manager().create_timer(func)
-    }
-
-    /// Adds a POSIX timer to the managed `posix_timers`, and allocate a timer ID for this timer.
-    /// Return the timer ID.
-    pub fn add_posix_timer(&self, posix_timer: Arc<Timer>) -> usize {
         let mut timers = self.posix_timers.lock();
         // Holding the lock of `posix_timers` is required to operate the `id_allocator`.
-        let timer_id = self.id_allocator.lock().alloc().unwrap();
+        let Some(timer_id) = self.id_allocator.lock().alloc() else {
+            return_errno_with_message!(Errno::EAGAIN, "too many POSIX timers");
+        };
         if timers.len() < timer_id + 1 {
             timers.resize(timer_id + 1, None);
         }
         // The ID allocated is not used by any other timers so this index in `timers`
         // must be `None`.
-        timers[timer_id] = Some(posix_timer);
-        timer_id
+        timers[timer_id] = Some(PosixTimer::new(
+            timer_id,
+            timer_manager,
+            new_notify(timer_id),
+        ));
+        Ok(timer_id)
     }
 
     /// Finds a POSIX timer by the input `timer_id`.
-    pub fn find_posix_timer(&self, timer_id: usize) -> Option<Arc<Timer>> {
+    pub fn find_posix_timer(&self, timer_id: usize) -> Option<Arc<PosixTimer>> {
         let timers = self.posix_timers.lock();
         if timer_id >= timers.len() {
             return None;
@@ -198,7 +222,7 @@ impl PosixTimerManager {
     }
 
     /// Removes the POSIX timer with the ID `timer_id`.
-    pub fn remove_posix_timer(&self, timer_id: usize) -> Option<Arc<Timer>> {
+    pub fn remove_posix_timer(&self, timer_id: usize) -> Option<Arc<PosixTimer>> {
         let mut timers = self.posix_timers.lock();
         if timer_id >= timers.len() {
             return None;
@@ -211,4 +235,16 @@ impl PosixTimerManager {
         }
         timer
     }
+
+    /// Removes and cancels all the POSIX timers.
+    pub fn clear_posix_timers(&self) {
+        let mut timers = self.posix_timers.lock();
+        let mut id_allocator = self.id_allocator.lock();
+        for (timer_id, timer) in timers.drain(..).enumerate() {
+            if let Some(timer) = timer {
+                timer.timer().cancel();
+                id_allocator.free(timer_id);
+            }
+        }
+    }
 }
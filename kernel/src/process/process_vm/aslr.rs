@@ -0,0 +1,126 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Address space layout randomization (ASLR).
+//!
+//! When a new program is executed, the top of its stack, the base of the mmap
+//! region, the load address of the position-independent executable (PIE), and
+//! the initial program break are moved by random numbers of pages, so that the
+//! addresses in a user process are hard to be guessed by attackers.
+//!
+//! The ranges of the random offsets follow the defaults of Linux on RISC-V. The
+//! 32-bit programs use smaller ranges, since their address spaces are smaller.
+
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use ostd::mm::MAX_USERSPACE_VADDR;
+
+use super::{heap::USER_HEAP_BASE, COMPAT_TASK_SIZE};
+use crate::{prelude::*, util::random::getrandom};
+
+/// The level of ASLR, which is controlled by `/proc/sys/kernel/randomize_va_space`.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, TryFromInt)]
+pub enum RandomizeVaSpace {
+    /// Nothing is randomized.
+    Disabled = 0,
+    /// The stack, the mmap region, the vDSO, and the PIE are randomized.
+    Conservative = 1,
+    /// The program break is randomized as well.
+    Full = 2,
+}
+
+static RANDOMIZE_VA_SPACE: AtomicU8 = AtomicU8::new(RandomizeVaSpace::Full as u8);
+
+/// Returns the current level of ASLR.
+pub fn randomize_va_space() -> RandomizeVaSpace {
+    RandomizeVaSpace::try_from(RANDOMIZE_VA_SPACE.load(Ordering::Relaxed)).unwrap()
+}
+
+/// Sets the level of ASLR, which takes effect when a new program is executed.
+pub fn set_randomize_va_space(level: RandomizeVaSpace) {
+    RANDOMIZE_VA_SPACE.store(level as u8, Ordering::Relaxed);
+}
+
+/// The number of the pages that are never used above the stack.
+///
+/// We do not want the stack top too close to the end of the user space. Any
+/// small value greater than zero will do.
+const NR_FIXED_STACK_PADDING_PAGES: usize = 7;
+/// The maximum number of the pages that the stack top is moved down.
+const MAX_STACK_RANDOM_PAGES: usize = 1 << 18;
+/// The maximum number of the pages that the stack top of a 32-bit program is
+/// moved down.
+const MAX_COMPAT_STACK_RANDOM_PAGES: usize = 1 << 11;
+
+/// The maximum number of the pages that the mmap base and the PIE are moved up.
+const MAX_MMAP_RANDOM_PAGES: usize = 1 << 18;
+/// The maximum number of the pages that the mmap base and the PIE of a 32-bit
+/// program are moved up.
+const MAX_COMPAT_MMAP_RANDOM_PAGES: usize = 1 << 8;
+
+/// The maximum number of the pages that the program break is moved up.
+const MAX_BRK_RANDOM_PAGES: usize = (1 << 30) / PAGE_SIZE;
+
+/// Returns the end of the user space of a new program.
+fn task_size(is_compat: bool) -> Vaddr {
+    if is_compat {
+        COMPAT_TASK_SIZE
+    } else {
+        MAX_USERSPACE_VADDR
+    }
+}
+
+/// Returns the top of the stack for a new program.
+pub(super) fn stack_top(is_compat: bool) -> Vaddr {
+    let max_pages = if is_compat {
+        MAX_COMPAT_STACK_RANDOM_PAGES
+    } else {
+        MAX_STACK_RANDOM_PAGES
+    };
+    let nr_random_pages = random_pages(RandomizeVaSpace::Conservative, max_pages);
+    task_size(is_compat) - PAGE_SIZE * (NR_FIXED_STACK_PADDING_PAGES + nr_random_pages)
+}
+
+/// Returns the base of the mmap region for a new program.
+///
+/// The mmap base before randomization is at one third of the user space.
+pub(super) fn mmap_base(is_compat: bool) -> Vaddr {
+    let base = (task_size(is_compat) / 3) & !(PAGE_SIZE - 1);
+    base + PAGE_SIZE * random_mmap_pages(is_compat)
+}
+
+/// Returns the preferred load address of the PIE of a new program.
+///
+/// The load address before randomization is at two thirds of the user space.
+pub(in crate::process) fn pie_base(is_compat: bool) -> Vaddr {
+    let base = (task_size(is_compat) / 3 * 2) & !(PAGE_SIZE - 1);
+    base + PAGE_SIZE * random_mmap_pages(is_compat)
+}
+
+fn random_mmap_pages(is_compat: bool) -> usize {
+    let max_pages = if is_compat {
+        MAX_COMPAT_MMAP_RANDOM_PAGES
+    } else {
+        MAX_MMAP_RANDOM_PAGES
+    };
+    random_pages(RandomizeVaSpace::Conservative, max_pages)
+}
+
+/// Returns the initial program break for a new program.
+pub(super) fn heap_base() -> Vaddr {
+    USER_HEAP_BASE + PAGE_SIZE * random_pages(RandomizeVaSpace::Full, MAX_BRK_RANDOM_PAGES)
+}
+
+/// Returns a random number of pages below `max_pages`, or zero if the current
+/// level of ASLR is lower than `level`.
+fn random_pages(level: RandomizeVaSpace, max_pages: usize) -> usize {
+    debug_assert!(max_pages.is_power_of_two());
+
+    if randomize_va_space() < level {
+        return 0;
+    }
+
+    let mut random = 0usize;
+    getrandom(random.as_bytes_mut()).unwrap();
+    random & (max_pages - 1)
+}
@@ -5,20 +5,22 @@ use core::sync::atomic::{AtomicUsize, Ordering};
 use align_ext::AlignExt;
 use aster_rights::Full;
 
+use super::aslr;
 use crate::{
     prelude::*,
+    process::ResourceType,
     vm::{perms::VmPerms, vmar::Vmar},
 };
 
-/// The base address of user heap
+/// The base address of user heap before randomization
 pub const USER_HEAP_BASE: Vaddr = 0x0000_0000_1000_0000;
 /// The max allowed size of user heap
 pub const USER_HEAP_SIZE_LIMIT: usize = 16 * 1024 * PAGE_SIZE; // 16 * 4MB
 
 #[derive(Debug)]
 pub struct Heap {
-    /// The lowest address of the heap
-    base: Vaddr,
+    /// The lowest address of the heap, which is randomized for each new program
+    base: AtomicUsize,
     /// The heap size limit
     limit: usize,
     /// The current heap highest address
@@ -28,7 +30,7 @@ pub struct Heap {
 impl Heap {
     pub const fn new() -> Self {
         Heap {
-            base: USER_HEAP_BASE,
+            base: AtomicUsize::new(USER_HEAP_BASE),
             limit: USER_HEAP_SIZE_LIMIT,
             current_heap_end: AtomicUsize::new(USER_HEAP_BASE),
         }
@@ -36,12 +38,12 @@ impl Heap {
 
     /// Initializes and maps the heap virtual memory.
     pub(super) fn alloc_and_map_vm(&self, root_vmar: &Vmar<Full>) -> Result<()> {
+        let base = aslr::heap_base();
+        self.base.store(base, Ordering::Relaxed);
+
         let vmar_map_options = {
             let perms = VmPerms::READ | VmPerms::WRITE;
-            root_vmar
-                .new_map(PAGE_SIZE, perms)
-                .unwrap()
-                .offset(self.base)
+            root_vmar.new_map(PAGE_SIZE, perms).unwrap().offset(base)
         };
         vmar_map_options.build()?;
 
@@ -57,7 +59,7 @@ impl Heap {
             root_vmar
                 .new_map(USER_HEAP_SIZE_LIMIT - PAGE_SIZE, perms)
                 .unwrap()
-                .offset(self.base + PAGE_SIZE)
+                .offset(base + PAGE_SIZE)
         };
         vmar_reserve_options.build()?;
 
@@ -71,9 +73,18 @@ impl Heap {
         match new_heap_end {
             None => Ok(self.current_heap_end.load(Ordering::Relaxed)),
             Some(new_heap_end) => {
-                if new_heap_end > self.base + self.limit {
+                let base = self.base();
+                if new_heap_end > base + self.limit {
                     return_errno_with_message!(Errno::ENOMEM, "heap size limit was met.");
                 }
+                let rlimit_data = ctx
+                    .process
+                    .resource_limits()
+                    .get_rlimit(ResourceType::RLIMIT_DATA)
+                    .get_cur();
+                if new_heap_end.saturating_sub(base) as u64 > rlimit_data {
+                    return_errno_with_message!(Errno::ENOMEM, "data size limit was met.");
+                }
                 let current_heap_end = self.current_heap_end.load(Ordering::Acquire);
 
                 if new_heap_end <= current_heap_end {
@@ -87,11 +98,11 @@ impl Heap {
                 // Remove the reserved space.
                 root_vmar.remove_mapping(current_heap_end..new_heap_end)?;
 
-                let old_size = current_heap_end - self.base;
-                let new_size = new_heap_end - self.base;
+                let old_size = current_heap_end - base;
+                let new_size = new_heap_end - base;
 
                 // Expand the heap.
-                root_vmar.resize_mapping(self.base, old_size, new_size)?;
+                root_vmar.resize_mapping(base, old_size, new_size)?;
 
                 self.current_heap_end.store(new_heap_end, Ordering::Release);
                 Ok(new_heap_end)
@@ -101,7 +112,31 @@ impl Heap {
 
     pub(super) fn set_uninitialized(&self) {
         self.current_heap_end
-            .store(self.base + PAGE_SIZE, Ordering::Relaxed);
+            .store(self.base() + PAGE_SIZE, Ordering::Relaxed);
+    }
+
+    /// Returns the lowest address of the heap, i.e., `start_brk`.
+    pub fn base(&self) -> Vaddr {
+        self.base.load(Ordering::Relaxed)
+    }
+
+    /// Returns the current program break.
+    pub fn current_heap_end(&self) -> Vaddr {
+        self.current_heap_end.load(Ordering::Relaxed)
+    }
+
+    /// Moves the heap to `base` with the program break at `heap_end`.
+    ///
+    /// This is used to restore a process from a checkpoint (see `prctl(PR_SET_MM)`). The
+    /// mappings of the heap are restored by the user space, so the heap can be expanded by
+    /// `brk` again only if the heap is mapped exactly at `base..heap_end`.
+    pub fn set_range(&self, base: Vaddr, heap_end: Vaddr) -> Result<()> {
+        if heap_end < base || heap_end > base + self.limit {
+            return_errno_with_message!(Errno::EINVAL, "the heap range is invalid");
+        }
+        self.base.store(base, Ordering::Relaxed);
+        self.current_heap_end.store(heap_end, Ordering::Release);
+        Ok(())
     }
 }
 
@@ -109,7 +144,7 @@ impl Clone for Heap {
     fn clone(&self) -> Self {
         let current_heap_end = self.current_heap_end.load(Ordering::Relaxed);
         Self {
-            base: self.base,
+            base: AtomicUsize::new(self.base()),
             limit: self.limit,
             current_heap_end: AtomicUsize::new(current_heap_end),
         }
@@ -15,17 +15,18 @@
 
 use core::{
     mem,
-    sync::atomic::{AtomicUsize, Ordering},
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
 };
 
 use align_ext::AlignExt;
 use aster_rights::Full;
-use ostd::mm::{vm_space::VmItem, UntypedMem, VmIo, MAX_USERSPACE_VADDR};
+use ostd::mm::{vm_space::VmItem, Infallible, UntypedMem, VmIo};
 
 use self::aux_vec::{AuxKey, AuxVec};
-use super::ProcessVmarGuard;
+use super::{aslr, MmLayout, ProcessVmarGuard};
 use crate::{
     prelude::*,
+    process::{Process, ResourceType},
     util::random::getrandom,
     vm::{
         perms::VmPerms,
@@ -38,6 +39,13 @@ pub mod aux_vec;
 
 /// Set the initial stack size to 8 megabytes, following the default Linux stack size limit.
 pub const INIT_STACK_SIZE: usize = 8 * 1024 * 1024; // 8 MB
+/// The min size of the user stack, which is the size that Linux expands the stack to
+/// when executing a new program.
+const MIN_STACK_SIZE: usize = 128 * 1024; // 128 KB
+/// The max size of the user stack, which is used if the stack size is unlimited.
+const MAX_STACK_SIZE: usize = 1024 * 1024 * 1024; // 1 GB
+/// The max size of the user stack of a 32-bit program, which has a smaller user space.
+const MAX_COMPAT_STACK_SIZE: usize = 256 * 1024 * 1024; // 256 MB
 
 /// The max number of arguments that can be used to creating a new process.
 pub const MAX_ARGV_NUMBER: usize = 128;
@@ -93,54 +101,48 @@ pub const MAX_ENV_LEN: usize = 128;
  *  |                     |
  *  +---------------------+ <------+ User stack default rlimit
  *  (low address)
+ *
+ * The pointers, the `argc`, and the auxiliary table entries are of 32 bits for a
+ * 32-bit program and of 64 bits for other programs.
  */
 
 /// The initial portion of the main stack of a process.
 pub struct InitStack {
-    /// The initial highest address.
+    /// The initial highest address, which is randomized for each new program.
     /// The stack grows down from this address
-    initial_top: Vaddr,
-    /// The max allowed stack size
-    max_size: usize,
+    initial_top: AtomicUsize,
+    /// The max allowed stack size, which is determined by `RLIMIT_STACK` for each new program.
+    max_size: AtomicUsize,
     /// The current stack pointer.
     /// Before initialized, `pos` points to the `initial_top`,
     /// After initialized, `pos` points to the user stack pointer(rsp)
     /// of the process.
     pos: Arc<AtomicUsize>,
+    /// Whether the program is a 32-bit program.
+    is_compat: AtomicBool,
 }
 
 impl Clone for InitStack {
     fn clone(&self) -> Self {
         Self {
-            initial_top: self.initial_top,
-            max_size: self.max_size,
+            initial_top: AtomicUsize::new(self.initial_top()),
+            max_size: AtomicUsize::new(self.max_size()),
             pos: Arc::new(AtomicUsize::new(self.pos.load(Ordering::Relaxed))),
+            is_compat: AtomicBool::new(self.is_compat()),
         }
     }
 }
 
 impl InitStack {
     pub(super) fn new() -> Self {
-        let nr_pages_padding = {
-            // We do not want the stack top too close to MAX_USERSPACE_VADDR.
-            // So we add this fixed padding. Any small value greater than zero will do.
-            const NR_FIXED_PADDING_PAGES: usize = 7;
-
-            // Some random padding pages are added as a simple measure to
-            // make the stack values of a buggy user program harder
-            // to be exploited by attackers.
-            let mut nr_random_padding_pages: u8 = 0;
-            getrandom(nr_random_padding_pages.as_bytes_mut()).unwrap();
-
-            nr_random_padding_pages as usize + NR_FIXED_PADDING_PAGES
-        };
-        let initial_top = MAX_USERSPACE_VADDR - PAGE_SIZE * nr_pages_padding;
+        let initial_top = aslr::stack_top(false);
         let max_size = INIT_STACK_SIZE;
 
         Self {
-            initial_top,
-            max_size,
+            initial_top: AtomicUsize::new(initial_top),
+            max_size: AtomicUsize::new(max_size),
             pos: Arc::new(AtomicUsize::new(initial_top)),
+            is_compat: AtomicBool::new(false),
         }
     }
 
@@ -155,25 +157,38 @@ impl InitStack {
     }
 
     /// Maps the VMO of the init stack and constructs a writer to initialize its content.
+    ///
+    /// If `is_compat` is true, the content is laid out for a 32-bit program.
     pub(super) fn map_and_write(
         &self,
         root_vmar: &Vmar<Full>,
         argv: Vec<CString>,
         envp: Vec<CString>,
         auxvec: AuxVec,
+        is_compat: bool,
+        mm_layout: &mut MmLayout,
     ) -> Result<()> {
+        self.initial_top
+            .store(aslr::stack_top(is_compat), Ordering::Relaxed);
+        let max_size = if is_compat {
+            stack_size_limit().min(MAX_COMPAT_STACK_SIZE)
+        } else {
+            stack_size_limit()
+        };
+        self.max_size.store(max_size, Ordering::Relaxed);
+        self.is_compat.store(is_compat, Ordering::Relaxed);
         self.set_uninitialized();
 
         let vmo = {
-            let vmo_options = VmoOptions::<Full>::new(self.max_size);
+            let vmo_options = VmoOptions::<Full>::new(self.max_size());
             vmo_options.alloc()?
         };
         let vmar_map_options = {
             let perms = VmPerms::READ | VmPerms::WRITE;
-            let map_addr = self.initial_top - self.max_size;
+            let map_addr = self.initial_top() - self.max_size();
             debug_assert!(map_addr % PAGE_SIZE == 0);
             root_vmar
-                .new_map(self.max_size, perms)?
+                .new_map(self.max_size(), perms)?
                 .offset(map_addr)
                 .vmo(vmo.dup().to_dyn())
         };
@@ -185,9 +200,10 @@ impl InitStack {
             argv,
             envp,
             auxvec,
-            map_addr: self.initial_top - self.max_size,
+            map_addr: self.initial_top() - self.max_size(),
+            is_compat,
         };
-        writer.write()
+        writer.write(mm_layout)
     }
 
     /// Constructs a reader to parse the content of an `InitStack`.
@@ -197,21 +213,52 @@ impl InitStack {
         InitStackReader {
             base: self.pos(),
             vmar,
-            map_addr: self.initial_top - self.max_size,
+            map_addr: self.initial_top() - self.max_size(),
+            is_compat: self.is_compat(),
         }
     }
 
     fn is_initialized(&self) -> bool {
-        self.pos() != self.initial_top
+        self.pos() != self.initial_top()
     }
 
     fn set_uninitialized(&self) {
-        self.pos.store(self.initial_top, Ordering::Relaxed);
+        self.pos.store(self.initial_top(), Ordering::Relaxed);
+    }
+
+    fn initial_top(&self) -> Vaddr {
+        self.initial_top.load(Ordering::Relaxed)
     }
 
     fn pos(&self) -> Vaddr {
         self.pos.load(Ordering::Relaxed)
     }
+
+    fn max_size(&self) -> usize {
+        self.max_size.load(Ordering::Relaxed)
+    }
+
+    fn is_compat(&self) -> bool {
+        self.is_compat.load(Ordering::Relaxed)
+    }
+}
+
+/// Returns the stack size of a new program, which is limited by the `RLIMIT_STACK` of the
+/// current process.
+fn stack_size_limit() -> usize {
+    let Some(process) = Process::current() else {
+        // The init process is created by a kernel thread.
+        return INIT_STACK_SIZE;
+    };
+
+    let rlimit_stack = process
+        .resource_limits()
+        .get_rlimit(ResourceType::RLIMIT_STACK)
+        .get_cur();
+    usize::try_from(rlimit_stack)
+        .unwrap_or(MAX_STACK_SIZE)
+        .clamp(MIN_STACK_SIZE, MAX_STACK_SIZE)
+        .align_down(PAGE_SIZE)
 }
 
 /// A writer to initialize the content of an `InitStack`.
@@ -223,19 +270,27 @@ struct InitStackWriter {
     auxvec: AuxVec,
     /// The mapping address of the `InitStack`.
     map_addr: usize,
+    /// Whether the words are of 32 bits.
+    is_compat: bool,
 }
 
 impl InitStackWriter {
-    fn write(mut self) -> Result<()> {
+    /// Writes the content, and records the addresses of the arguments, the environment
+    /// variables and the stack pointer in `mm_layout`.
+    fn write(mut self, mm_layout: &mut MmLayout) -> Result<()> {
         // FIXME: Some OSes may put the first page of executable file here
         // for interpreting elf headers.
 
         let argc = self.argv.len() as u64;
 
         // Write envp string
+        mm_layout.env_end = self.pos();
         let envp_pointers = self.write_envp_strings()?;
+        mm_layout.env_start = self.pos();
         // Write argv string
+        mm_layout.arg_end = self.pos();
         let argv_pointers = self.write_argv_strings()?;
+        mm_layout.arg_start = self.pos();
         // Generate random values for auxvec
         let random_value_pointer = {
             let random_value = generate_random_for_aux_vec();
@@ -249,7 +304,9 @@ impl InitStackWriter {
         self.write_argv_pointers(argv_pointers)?;
 
         // write argc
-        self.write_u64(argc)?;
+        self.write_word(argc)?;
+        mm_layout.start_stack = self.pos();
+        mm_layout.auxv = self.auxv_words();
 
         // Ensure stack top is 16-bytes aligned
         debug_assert_eq!(self.pos() & !0xf, self.pos());
@@ -278,26 +335,27 @@ impl InitStackWriter {
     }
 
     /// Libc ABI requires 16-byte alignment of the stack entrypoint.
-    /// Current position of the stack is 8-byte aligned already, insert 8 byte
-    /// to meet the requirement if necessary.
+    /// Current position of the stack is aligned to the word size after a word
+    /// is written, insert more words to meet the requirement if necessary.
     fn adjust_stack_alignment(&self, envp_pointers: &[u64], argv_pointers: &[u64]) -> Result<()> {
-        // Ensure 8-byte alignment
-        self.write_u64(0)?;
-        let auxvec_size = (self.auxvec.table().len() + 1) * (mem::size_of::<u64>() * 2);
-        let envp_pointers_size = (envp_pointers.len() + 1) * mem::size_of::<u64>();
-        let argv_pointers_size = (argv_pointers.len() + 1) * mem::size_of::<u64>();
-        let argc_size = mem::size_of::<u64>();
+        // Ensure the word alignment
+        self.write_word(0)?;
+        let word_size = self.word_size();
+        let auxvec_size = (self.auxvec.table().len() + 1) * (word_size * 2);
+        let envp_pointers_size = (envp_pointers.len() + 1) * word_size;
+        let argv_pointers_size = (argv_pointers.len() + 1) * word_size;
+        let argc_size = word_size;
         let to_write_size = auxvec_size + envp_pointers_size + argv_pointers_size + argc_size;
-        if (self.pos() - to_write_size) % 16 != 0 {
-            self.write_u64(0)?;
+        while (self.pos() - to_write_size) % 16 != 0 {
+            self.write_word(0)?;
         }
         Ok(())
     }
 
     fn write_aux_vec(&self) -> Result<()> {
         // Write NULL auxiliary
-        self.write_u64(0)?;
-        self.write_u64(AuxKey::AT_NULL as u64)?;
+        self.write_word(0)?;
+        self.write_word(AuxKey::AT_NULL as u64)?;
         // Write Auxiliary vectors
         let aux_vec: Vec<_> = self
             .auxvec
@@ -306,43 +364,67 @@ impl InitStackWriter {
             .map(|(aux_key, aux_value)| (*aux_key, *aux_value))
             .collect();
         for (aux_key, aux_value) in aux_vec.iter() {
-            self.write_u64(*aux_value)?;
-            self.write_u64(*aux_key as u64)?;
+            self.write_word(*aux_value)?;
+            self.write_word(*aux_key as u64)?;
         }
         Ok(())
     }
 
+    fn auxv_words(&self) -> Vec<u64> {
+        let mut words = Vec::with_capacity((self.auxvec.table().len() + 1) * 2);
+        for (aux_key, aux_value) in self.auxvec.table().iter() {
+            words.push(*aux_key as u64);
+            words.push(*aux_value);
+        }
+        words.extend([AuxKey::AT_NULL as u64, 0]);
+        words
+    }
+
     fn write_envp_pointers(&self, mut envp_pointers: Vec<u64>) -> Result<()> {
         // write NULL pointer
-        self.write_u64(0)?;
+        self.write_word(0)?;
         // write envp pointers
         envp_pointers.reverse();
         for envp_pointer in envp_pointers {
-            self.write_u64(envp_pointer)?;
+            self.write_word(envp_pointer)?;
         }
         Ok(())
     }
 
     fn write_argv_pointers(&self, mut argv_pointers: Vec<u64>) -> Result<()> {
         // write 0
-        self.write_u64(0)?;
+        self.write_word(0)?;
         // write argv pointers
         argv_pointers.reverse();
         for argv_pointer in argv_pointers {
-            self.write_u64(argv_pointer)?;
+            self.write_word(argv_pointer)?;
         }
         Ok(())
     }
 
-    /// Writes u64 to the stack.
+    /// Writes a word (i.e., u32 for a 32-bit program or u64 otherwise) to the stack.
     /// Returns the writing address
-    fn write_u64(&self, val: u64) -> Result<u64> {
-        let start_address = (self.pos() - 8).align_down(8);
+    fn write_word(&self, val: u64) -> Result<u64> {
+        let word_size = self.word_size();
+        let start_address = (self.pos() - word_size).align_down(word_size);
         self.pos.store(start_address, Ordering::Relaxed);
-        self.vmo.write_val(start_address - self.map_addr, &val)?;
+        if self.is_compat {
+            self.vmo
+                .write_val(start_address - self.map_addr, &(val as u32))?;
+        } else {
+            self.vmo.write_val(start_address - self.map_addr, &val)?;
+        }
         Ok(self.pos() as u64)
     }
 
+    fn word_size(&self) -> usize {
+        if self.is_compat {
+            mem::size_of::<u32>()
+        } else {
+            mem::size_of::<u64>()
+        }
+    }
+
     /// Writes a CString including the ending null byte to the stack.
     /// Returns the writing address
     fn write_cstring(&self, val: &CString) -> Result<u64> {
@@ -377,6 +459,8 @@ pub struct InitStackReader<'a> {
     vmar: ProcessVmarGuard<'a>,
     /// The mapping address of the `InitStack`.
     map_addr: usize,
+    /// Whether the words are of 32 bits.
+    is_compat: bool,
 }
 
 impl InitStackReader<'_> {
@@ -391,7 +475,9 @@ impl InitStackReader<'_> {
             return_errno_with_message!(Errno::EACCES, "Page not accessible");
         };
 
-        let argc = frame.read_val::<u64>(stack_base - page_base_addr)?;
+        let mut argc_reader = frame.reader();
+        argc_reader.skip(stack_base - page_base_addr);
+        let argc = self.read_word(&mut argc_reader)? as u64;
         if argc > MAX_ARGV_NUMBER as u64 {
             return_errno_with_message!(Errno::EINVAL, "argc is corrupted");
         }
@@ -404,7 +490,7 @@ impl InitStackReader<'_> {
         let argc = self.argc()? as usize;
         // The reading offset in the initial stack is:
         // the initial stack bottom address + the size of `argc` in memory
-        let read_offset = self.init_stack_bottom() + size_of::<usize>();
+        let read_offset = self.init_stack_bottom() + self.word_size();
 
         let mut argv = Vec::with_capacity(argc);
         let page_base_addr = read_offset.align_down(PAGE_SIZE);
@@ -419,7 +505,7 @@ impl InitStackReader<'_> {
         arg_ptr_reader.skip(read_offset - page_base_addr);
         for _ in 0..argc {
             let arg = {
-                let arg_ptr = arg_ptr_reader.read_val::<Vaddr>()?;
+                let arg_ptr = self.read_word(&mut arg_ptr_reader)?;
                 let arg_offset = arg_ptr
                     .checked_sub(page_base_addr)
                     .ok_or_else(|| Error::with_message(Errno::EINVAL, "arg_ptr is corrupted"))?;
@@ -438,13 +524,13 @@ impl InitStackReader<'_> {
         let argc = self.argc()? as usize;
         // The reading offset in the initial stack is:
         // the initial stack bottom address
-        // + the size of argc(8)
-        // + the size of arg pointer(8) * the number of arg(argc)
-        // + the size of null pointer(8)
+        // + the size of argc(word)
+        // + the size of arg pointer(word) * the number of arg(argc)
+        // + the size of null pointer(word)
         let read_offset = self.init_stack_bottom()
-            + size_of::<usize>()
-            + size_of::<usize>() * argc
-            + size_of::<usize>();
+            + self.word_size()
+            + self.word_size() * argc
+            + self.word_size();
 
         let mut envp = Vec::new();
         let page_base_addr = read_offset.align_down(PAGE_SIZE);
@@ -459,7 +545,7 @@ impl InitStackReader<'_> {
         envp_ptr_reader.skip(read_offset - page_base_addr);
         for _ in 0..MAX_ENVP_NUMBER {
             let env = {
-                let envp_ptr = envp_ptr_reader.read_val::<Vaddr>()?;
+                let envp_ptr = self.read_word(&mut envp_ptr_reader)?;
 
                 if envp_ptr == 0 {
                     break;
@@ -482,4 +568,21 @@ impl InitStackReader<'_> {
     pub const fn init_stack_bottom(&self) -> Vaddr {
         self.base
     }
+
+    /// Reads a word (i.e., u32 for a 32-bit program or u64 otherwise).
+    fn read_word(&self, reader: &mut VmReader<'_, Infallible>) -> Result<usize> {
+        if self.is_compat {
+            Ok(reader.read_val::<u32>()? as usize)
+        } else {
+            Ok(reader.read_val::<usize>()?)
+        }
+    }
+
+    fn word_size(&self) -> usize {
+        if self.is_compat {
+            size_of::<u32>()
+        } else {
+            size_of::<usize>()
+        }
+    }
 }
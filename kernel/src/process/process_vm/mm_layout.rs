@@ -0,0 +1,29 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use crate::prelude::*;
+
+/// The addresses of the segments of a program and its initial stack, which are
+/// the `start_code`, `arg_start`, etc. fields of `struct mm_struct` in Linux.
+///
+/// They are set when the program is loaded. They can also be changed by
+/// `prctl(PR_SET_MM)`, e.g., when a process is restored from a checkpoint and
+/// its mappings are rebuilt at the original addresses.
+///
+/// The heap is described by the [`Heap`] instead.
+///
+/// [`Heap`]: super::Heap
+#[derive(Debug, Clone, Default)]
+pub struct MmLayout {
+    pub start_code: Vaddr,
+    pub end_code: Vaddr,
+    pub start_data: Vaddr,
+    pub end_data: Vaddr,
+    /// The address of `argc` on the initial stack
+    pub start_stack: Vaddr,
+    pub arg_start: Vaddr,
+    pub arg_end: Vaddr,
+    pub env_start: Vaddr,
+    pub env_end: Vaddr,
+    /// The auxiliary vector as the key-value pairs of words, which ends with `AT_NULL`
+    pub auxv: Vec<u64>,
+}
@@ -9,27 +9,33 @@
 //! the basic info of process level vm segments,
 //! like init stack and heap.
 
+mod aslr;
 mod heap;
 mod init_stack;
+mod mm_layout;
 
 use aster_rights::Full;
 pub use heap::Heap;
-use ostd::{sync::MutexGuard, task::disable_preempt};
+use ostd::{mm::MAX_USERSPACE_VADDR, sync::MutexGuard, task::disable_preempt};
 
+pub(super) use self::aslr::pie_base;
 pub use self::{
+    aslr::{randomize_va_space, set_randomize_va_space, RandomizeVaSpace},
     heap::USER_HEAP_SIZE_LIMIT,
     init_stack::{
         aux_vec::{AuxKey, AuxVec},
         InitStack, InitStackReader, INIT_STACK_SIZE, MAX_ARGV_NUMBER, MAX_ARG_LEN, MAX_ENVP_NUMBER,
         MAX_ENV_LEN,
     },
+    mm_layout::MmLayout,
 };
 use crate::{prelude::*, vm::vmar::Vmar};
 
 /*
  * The user's virtual memory space layout looks like below.
- * TODO: The layout of the userheap does not match the current implementation,
- * And currently the initial program break is a fixed value.
+ * TODO: The layout of the userheap does not match the current implementation.
+ * The stack, the mmap spaces, and the original program break are randomized
+ * for each new program, see the `aslr` module.
  *
  *  (high address)
  *  +---------------------+ <------+ The top of Vmar, which is the highest address usable
@@ -60,13 +66,20 @@ use crate::{prelude::*, vm::vmar::Vmar};
  *  |                     |          64 KiB unusable space
  *  +---------------------+
  *  (low address)
+ *
+ * For a 32-bit program, the highest usable address is `COMPAT_TASK_SIZE`
+ * instead of the top of Vmar.
  */
 
+/// The end of the user space of a 32-bit program, i.e., `TASK_SIZE_32` of Linux.
+pub const COMPAT_TASK_SIZE: Vaddr = 0x8000_0000;
+
 /// The process user space virtual memory
 pub struct ProcessVm {
     root_vmar: Mutex<Option<Vmar<Full>>>,
     init_stack: InitStack,
     heap: Heap,
+    mm_layout: Mutex<MmLayout>,
 }
 
 /// A guard to the [`Vmar`] used by a process.
@@ -83,6 +96,11 @@ impl ProcessVmarGuard<'_> {
         self.inner.as_ref().unwrap()
     }
 
+    /// Gets a reference to the process VMAR, or `None` if the process has exited.
+    pub fn try_get(&self) -> Option<&Vmar<Full>> {
+        self.inner.as_ref()
+    }
+
     /// Sets a new VMAR for the binding process.
     ///
     /// If the `new_vmar` is `None`, this method will remove the
@@ -99,6 +117,7 @@ impl Clone for ProcessVm {
             root_vmar: Mutex::new(Some(root_vmar.get().dup().unwrap())),
             init_stack: self.init_stack.clone(),
             heap: self.heap.clone(),
+            mm_layout: Mutex::new(self.mm_layout.lock().clone()),
         }
     }
 }
@@ -109,11 +128,12 @@ impl ProcessVm {
         let root_vmar = Vmar::<Full>::new_root();
         let init_stack = InitStack::new();
         let heap = Heap::new();
-        heap.alloc_and_map_vm(&root_vmar).unwrap();
+        init_layout(&root_vmar, &heap);
         Self {
             root_vmar: Mutex::new(Some(root_vmar)),
             heap,
             init_stack,
+            mm_layout: Mutex::new(MmLayout::default()),
         }
     }
 
@@ -127,6 +147,7 @@ impl ProcessVm {
             root_vmar,
             heap: other.heap.clone(),
             init_stack: other.init_stack.clone(),
+            mm_layout: Mutex::new(other.mm_layout.lock().clone()),
         })
     }
 
@@ -153,10 +174,30 @@ impl ProcessVm {
         argv: Vec<CString>,
         envp: Vec<CString>,
         aux_vec: AuxVec,
+        is_compat: bool,
     ) -> Result<()> {
         let root_vmar: ProcessVmarGuard<'_> = self.lock_root_vmar();
-        self.init_stack
-            .map_and_write(root_vmar.get(), argv, envp, aux_vec)
+        self.init_stack.map_and_write(
+            root_vmar.get(),
+            argv,
+            envp,
+            aux_vec,
+            is_compat,
+            &mut self.mm_layout.lock(),
+        )
+    }
+
+    /// Returns the addresses of the segments and the initial stack of the program.
+    pub fn mm_layout(&self) -> &Mutex<MmLayout> {
+        &self.mm_layout
+    }
+
+    /// Moves the mmap region of a new program below `COMPAT_TASK_SIZE`, since the program is
+    /// a 32-bit program.
+    pub(super) fn set_compat_layout(&self) {
+        let root_vmar = self.lock_root_vmar();
+        root_vmar.get().set_mmap_base(aslr::mmap_base(true));
+        root_vmar.get().set_mmap_top(COMPAT_TASK_SIZE);
     }
 
     pub(super) fn heap(&self) -> &Heap {
@@ -167,7 +208,7 @@ impl ProcessVm {
     pub fn clear_and_map(&self) {
         let root_vmar = self.lock_root_vmar();
         root_vmar.get().clear().unwrap();
-        self.heap.alloc_and_map_vm(&root_vmar.get()).unwrap();
+        init_layout(root_vmar.get(), &self.heap);
     }
 }
 
@@ -183,5 +224,12 @@ pub fn renew_vm_and_map(ctx: &Context) {
     root_vmar.set_vmar(Some(new_vmar));
     drop(guard);
 
-    process_vm.heap.alloc_and_map_vm(root_vmar.get()).unwrap();
+    init_layout(root_vmar.get(), &process_vm.heap);
+}
+
+/// Randomizes the layout of the empty VMAR for a new program and then maps the heap VMO.
+fn init_layout(root_vmar: &Vmar<Full>, heap: &Heap) {
+    root_vmar.set_mmap_base(aslr::mmap_base(false));
+    root_vmar.set_mmap_top(MAX_USERSPACE_VADDR);
+    heap.alloc_and_map_vm(root_vmar).unwrap();
 }
@@ -0,0 +1,396 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Miscellaneous binary formats (binfmt_misc).
+//!
+//! An interpreter can be registered for the executables that are recognized either by a magic
+//! number at some offset or by a file name extension. Such an executable is then run by the
+//! interpreter, which receives the path of the executable as its first argument. This is how,
+//! e.g., the programs of foreign architectures are run with `qemu-user`.
+//!
+//! The handlers are registered by writing lines like
+//! `:name:type:offset:magic:mask:interpreter:flags` to `/proc/sys/fs/binfmt_misc/register`,
+//! which is always available without mounting the file system.
+//!
+//! Reference: <https://docs.kernel.org/admin-guide/binfmt-misc.html>
+
+use alloc::format;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::{
+    fs::{
+        fs_resolver::{FsPath, FsResolver, AT_FDCWD},
+        path::Dentry,
+    },
+    prelude::*,
+    process::posix_thread::AsPosixThread,
+};
+
+/// The number of the bytes at the start of an executable that can be matched against.
+const BINPRM_BUF_SIZE: usize = 256;
+
+/// The maximum length of the name of a handler.
+const MAX_NAME_LEN: usize = 255;
+
+/// A registered handler of a binary format.
+#[derive(Debug)]
+pub struct BinfmtEntry {
+    name: String,
+    matcher: Matcher,
+    interpreter: String,
+    /// The interpreter, which is opened at registration if the `F` flag is set.
+    interpreter_file: Option<Dentry>,
+    flags: BinfmtFlags,
+    is_enabled: AtomicBool,
+}
+
+#[derive(Debug)]
+enum Matcher {
+    Magic {
+        offset: usize,
+        magic: Vec<u8>,
+        mask: Option<Vec<u8>>,
+    },
+    Extension(String),
+}
+
+bitflags! {
+    struct BinfmtFlags: u8 {
+        /// Passes the original `argv[0]` to the interpreter after the path of the executable.
+        const PRESERVE_ARGV0 = 1 << 0;
+        /// Opens the interpreter at registration, so it is found in any mount namespace or
+        /// after `chroot`.
+        const FIX_BINARY = 1 << 1;
+    }
+}
+
+static ENTRIES: RwLock<Vec<Arc<BinfmtEntry>>> = RwLock::new(Vec::new());
+
+static IS_ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Registers a handler from the line that is written to the `register` file.
+pub fn register(line: &[u8]) -> Result<()> {
+    let entry = BinfmtEntry::parse(line)?;
+
+    let mut entries = ENTRIES.write();
+    if entries.iter().any(|other| other.name == entry.name) {
+        return_errno_with_message!(Errno::EEXIST, "the binary format is already registered");
+    }
+    entries.push(Arc::new(entry));
+    Ok(())
+}
+
+/// Unregisters the handler of the name.
+pub fn unregister(name: &str) -> Result<()> {
+    let mut entries = ENTRIES.write();
+    let Some(index) = entries.iter().position(|entry| entry.name == name) else {
+        return_errno_with_message!(Errno::ENOENT, "the binary format is not registered");
+    };
+    entries.remove(index);
+    Ok(())
+}
+
+/// Unregisters all the handlers.
+pub fn unregister_all() {
+    ENTRIES.write().clear();
+}
+
+/// Returns the handler of the name.
+pub fn lookup_entry(name: &str) -> Option<Arc<BinfmtEntry>> {
+    ENTRIES
+        .read()
+        .iter()
+        .find(|entry| entry.name == name)
+        .cloned()
+}
+
+/// Returns the names of all the handlers.
+pub fn entry_names() -> Vec<String> {
+    ENTRIES
+        .read()
+        .iter()
+        .map(|entry| entry.name.clone())
+        .collect()
+}
+
+/// Returns whether the handlers are used when executing programs.
+pub fn is_enabled() -> bool {
+    IS_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Sets whether the handlers are used when executing programs.
+pub fn set_enabled(is_enabled: bool) {
+    IS_ENABLED.store(is_enabled, Ordering::Relaxed);
+}
+
+/// Finds the first enabled handler that recognizes the executable with the header and the path.
+pub(super) fn find_handler(file_header: &[u8], path: &str) -> Option<Arc<BinfmtEntry>> {
+    if !is_enabled() {
+        return None;
+    }
+
+    ENTRIES
+        .read()
+        .iter()
+        .find(|entry| entry.is_enabled() && entry.matches(file_header, path))
+        .cloned()
+}
+
+impl BinfmtEntry {
+    fn parse(line: &[u8]) -> Result<Self> {
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        let Some((&delimiter, line)) = line.split_first() else {
+            return_errno_with_message!(Errno::EINVAL, "the registration is empty");
+        };
+        let mut fields = line.split(|&c| c == delimiter);
+        let mut next_field = || {
+            fields
+                .next()
+                .ok_or_else(|| Error::with_message(Errno::EINVAL, "too few fields"))
+        };
+
+        let name = field_to_str(next_field()?)?;
+        if name.is_empty() || name == "." || name == ".." || name.contains('/') {
+            return_errno_with_message!(Errno::EINVAL, "invalid name");
+        }
+        if name.len() > MAX_NAME_LEN {
+            return_errno_with_message!(Errno::ENAMETOOLONG, "the name is too long");
+        }
+
+        let type_ = next_field()?;
+        let offset = next_field()?;
+        let magic = next_field()?;
+        let mask = next_field()?;
+        let matcher = match type_ {
+            b"M" => {
+                let offset = if offset.is_empty() {
+                    0
+                } else {
+                    field_to_str(offset)?
+                        .parse::<usize>()
+                        .map_err(|_| Error::with_message(Errno::EINVAL, "invalid offset"))?
+                };
+                let magic = unescape(magic)?;
+                let mask = if mask.is_empty() {
+                    None
+                } else {
+                    Some(unescape(mask)?)
+                };
+                if magic.is_empty() {
+                    return_errno_with_message!(Errno::EINVAL, "the magic is empty");
+                }
+                if mask.as_ref().is_some_and(|mask| mask.len() != magic.len()) {
+                    return_errno_with_message!(
+                        Errno::EINVAL,
+                        "the mask and the magic differ in length"
+                    );
+                }
+                if offset + magic.len() > BINPRM_BUF_SIZE {
+                    return_errno_with_message!(Errno::EINVAL, "the magic is out of range");
+                }
+                Matcher::Magic {
+                    offset,
+                    magic,
+                    mask,
+                }
+            }
+            b"E" => {
+                if !offset.is_empty() || !mask.is_empty() {
+                    return_errno_with_message!(
+                        Errno::EINVAL,
+                        "the offset and the mask are not allowed for an extension"
+                    );
+                }
+                let extension = field_to_str(magic)?;
+                if extension.is_empty() || extension.contains('/') {
+                    return_errno_with_message!(Errno::EINVAL, "invalid extension");
+                }
+                Matcher::Extension(extension)
+            }
+            _ => return_errno_with_message!(Errno::EINVAL, "invalid type"),
+        };
+
+        let interpreter = field_to_str(next_field()?)?;
+        if interpreter.is_empty() {
+            return_errno_with_message!(Errno::EINVAL, "the interpreter is empty");
+        }
+
+        let mut flags = BinfmtFlags::empty();
+        for &flag in fields.next().unwrap_or_default() {
+            match flag {
+                b'P' => flags |= BinfmtFlags::PRESERVE_ARGV0,
+                b'F' => flags |= BinfmtFlags::FIX_BINARY,
+                b'O' | b'C' => {
+                    return_errno_with_message!(
+                        Errno::EINVAL,
+                        "passing the executable as a file descriptor is not supported"
+                    )
+                }
+                _ => return_errno_with_message!(Errno::EINVAL, "invalid flags"),
+            }
+        }
+        if fields.next().is_some() {
+            return_errno_with_message!(Errno::EINVAL, "too many fields");
+        }
+
+        let interpreter_file = if flags.contains(BinfmtFlags::FIX_BINARY) {
+            let current = current_thread!();
+            let posix_thread = current.as_posix_thread().unwrap();
+            let fs_resolver = posix_thread.fs().resolver().read();
+            Some(fs_resolver.lookup(&FsPath::new(AT_FDCWD, &interpreter)?)?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            name,
+            matcher,
+            interpreter,
+            interpreter_file,
+            flags,
+            is_enabled: AtomicBool::new(true),
+        })
+    }
+
+    /// Returns the name of the handler.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns whether the handler is used when executing programs.
+    pub fn is_enabled(&self) -> bool {
+        self.is_enabled.load(Ordering::Relaxed)
+    }
+
+    /// Sets whether the handler is used when executing programs.
+    pub fn set_enabled(&self, is_enabled: bool) {
+        self.is_enabled.store(is_enabled, Ordering::Relaxed);
+    }
+
+    /// Returns the path of the interpreter.
+    pub(super) fn interpreter(&self) -> &str {
+        &self.interpreter
+    }
+
+    /// Opens the interpreter, unless it has been opened at registration.
+    pub(super) fn interpreter_file(&self, fs_resolver: &FsResolver) -> Result<Dentry> {
+        if let Some(interpreter_file) = self.interpreter_file.as_ref() {
+            return Ok(interpreter_file.clone());
+        }
+        fs_resolver.lookup(&FsPath::new(AT_FDCWD, &self.interpreter)?)
+    }
+
+    /// Returns whether the original `argv[0]` is passed to the interpreter.
+    pub(super) fn preserves_argv0(&self) -> bool {
+        self.flags.contains(BinfmtFlags::PRESERVE_ARGV0)
+    }
+
+    fn matches(&self, file_header: &[u8], path: &str) -> bool {
+        match &self.matcher {
+            Matcher::Magic {
+                offset,
+                magic,
+                mask,
+            } => {
+                let Some(bytes) = file_header.get(*offset..*offset + magic.len()) else {
+                    return false;
+                };
+                match mask {
+                    Some(mask) => bytes
+                        .iter()
+                        .zip(mask)
+                        .zip(magic)
+                        .all(|((byte, mask), magic)| byte & mask == *magic),
+                    None => bytes == magic.as_slice(),
+                }
+            }
+            Matcher::Extension(extension) => {
+                let file_name = path.rsplit('/').next().unwrap_or(path);
+                file_name
+                    .rsplit_once('.')
+                    .is_some_and(|(_, file_extension)| file_extension == extension)
+            }
+        }
+    }
+
+    /// Returns the content of the file of the handler, in the format of Linux.
+    pub fn status(&self) -> String {
+        let mut status = String::new();
+
+        status.push_str(if self.is_enabled() {
+            "enabled\n"
+        } else {
+            "disabled\n"
+        });
+        status.push_str(&format!("interpreter {}\n", self.interpreter));
+
+        status.push_str("flags: ");
+        if self.flags.contains(BinfmtFlags::PRESERVE_ARGV0) {
+            status.push('P');
+        }
+        if self.flags.contains(BinfmtFlags::FIX_BINARY) {
+            status.push('F');
+        }
+        status.push('\n');
+
+        match &self.matcher {
+            Matcher::Magic {
+                offset,
+                magic,
+                mask,
+            } => {
+                status.push_str(&format!("offset {}\n", offset));
+                status.push_str(&format!("magic {}\n", to_hex(magic)));
+                if let Some(mask) = mask {
+                    status.push_str(&format!("mask {}\n", to_hex(mask)));
+                }
+            }
+            Matcher::Extension(extension) => {
+                status.push_str(&format!("extension .{}\n", extension));
+            }
+        }
+
+        status
+    }
+}
+
+fn field_to_str(field: &[u8]) -> Result<String> {
+    core::str::from_utf8(field)
+        .map(String::from)
+        .map_err(|_| Error::with_message(Errno::EINVAL, "the field is not valid UTF-8"))
+}
+
+/// Decodes the `\xHH` escapes in the magic and the mask.
+fn unescape(field: &[u8]) -> Result<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(field.len());
+
+    let mut rest = field;
+    while let Some((&byte, tail)) = rest.split_first() {
+        rest = tail;
+        if byte != b'\\' {
+            bytes.push(byte);
+            continue;
+        }
+
+        match rest {
+            [b'x', high, low, tail @ ..] => {
+                let digits = core::str::from_utf8(&[*high, *low])
+                    .ok()
+                    .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                    .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid escape"))?;
+                bytes.push(digits);
+                rest = tail;
+            }
+            [escaped, tail @ ..] => {
+                bytes.push(*escaped);
+                rest = tail;
+            }
+            [] => return_errno_with_message!(Errno::EINVAL, "incomplete escape"),
+        }
+    }
+
+    Ok(bytes)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
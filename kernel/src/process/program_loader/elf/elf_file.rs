@@ -36,9 +36,18 @@ impl Elf {
                 .map_err(|_| Error::with_message(Errno::ENOEXEC, "parse program header fails"))?;
             let ph64 = match program_header {
                 xmas_elf::program::ProgramHeader::Ph64(ph64) => *ph64,
-                xmas_elf::program::ProgramHeader::Ph32(_) => {
-                    return_errno_with_message!(Errno::ENOEXEC, "Not 64 byte executable")
-                }
+                // The program headers of a 32-bit executable are widened, so that they can
+                // be handled in the same way.
+                xmas_elf::program::ProgramHeader::Ph32(ph32) => ProgramHeader64 {
+                    type_: ph32.type_,
+                    flags: ph32.flags,
+                    offset: ph32.offset as u64,
+                    virtual_addr: ph32.virtual_addr as u64,
+                    physical_addr: ph32.physical_addr as u64,
+                    file_size: ph32.file_size as u64,
+                    mem_size: ph32.mem_size as u64,
+                    align: ph32.align as u64,
+                },
             };
             program_headers.push(ph64);
         }
@@ -84,6 +93,11 @@ impl Elf {
         );
     }
 
+    /// whether the elf is a 32-bit executable that runs on the 64-bit kernel
+    pub fn is_compat(&self) -> bool {
+        self.elf_header.pt1.class() == header::Class::ThirtyTwo
+    }
+
     /// whether the elf is a shared object
     pub fn is_shared_object(&self) -> bool {
         self.elf_header.pt2.type_.as_type() == header::Type::SharedObject
@@ -156,7 +170,7 @@ impl ElfHeader {
                     sh_str_index: *sh_str_index,
                 }
             }
-            _ => return_errno_with_message!(Errno::ENOEXEC, "parse elf header failed"),
+            HeaderPt2::Header32(header_pt2) => HeaderPt2_64::from_header32(header_pt2),
         };
         Ok(ElfHeader { pt1, pt2 })
     }
@@ -185,16 +199,39 @@ pub struct HeaderPt2_64 {
     pub sh_str_index: u16,
 }
 
+impl HeaderPt2_64 {
+    /// Widens the header of a 32-bit executable.
+    fn from_header32(header_pt2: &HeaderPt2_<u32>) -> Self {
+        Self {
+            type_: header_pt2.type_,
+            machine: header_pt2.machine,
+            version: header_pt2.version,
+            entry_point: header_pt2.entry_point as u64,
+            ph_offset: header_pt2.ph_offset as u64,
+            sh_offset: header_pt2.sh_offset as u64,
+            flags: header_pt2.flags,
+            header_size: header_pt2.header_size,
+            ph_entry_size: header_pt2.ph_entry_size,
+            ph_count: header_pt2.ph_count,
+            sh_entry_size: header_pt2.sh_entry_size,
+            sh_count: header_pt2.sh_count,
+            sh_str_index: header_pt2.sh_str_index,
+        }
+    }
+}
+
 fn check_elf_header(elf_header: &ElfHeader) -> Result<()> {
     #[cfg(target_arch = "riscv64")]
     const EXPECTED_ELF_MACHINE: header::Machine = header::Machine::RISC_V;
     #[cfg(target_arch = "x86_64")]
     const EXPECTED_ELF_MACHINE: header::Machine = header::Machine::X86_64;
 
-    // 64bit
-    debug_assert_eq!(elf_header.pt1.class(), header::Class::SixtyFour);
-    if elf_header.pt1.class() != header::Class::SixtyFour {
-        return_errno_with_message!(Errno::ENOEXEC, "Not 64 byte executable");
+    // 64bit, or 32bit if the CPU can run 32-bit programs
+    match elf_header.pt1.class() {
+        header::Class::SixtyFour => {}
+        #[cfg(target_arch = "riscv64")]
+        header::Class::ThirtyTwo if ostd::cpu::context::is_compat_mode_supported() => {}
+        _ => return_errno_with_message!(Errno::ENOEXEC, "Not 64 byte executable"),
     }
     // little endian
     debug_assert_eq!(elf_header.pt1.data(), header::Data::LittleEndian);
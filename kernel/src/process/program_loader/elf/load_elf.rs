@@ -6,12 +6,13 @@
 //! When create a process from elf file, we will use the elf_load_info to construct the VmSpace
 
 use align_ext::AlignExt;
-use aster_rights::Full;
+use aster_rights::{Full, ReadOp};
 use ostd::mm::VmIo;
 use xmas_elf::program::{self, ProgramHeader64};
 
 use super::elf_file::Elf;
 use crate::{
+    arch::cpu::hwcap,
     fs::{
         fs_resolver::{FsPath, FsResolver, AT_FDCWD},
         path::Dentry,
@@ -19,9 +20,10 @@ use crate::{
     prelude::*,
     process::{
         posix_thread::do_exit_group,
-        process_vm::{AuxKey, AuxVec, ProcessVm},
-        TermStatus,
+        process_vm::{pie_base, AuxKey, AuxVec, ProcessVm},
+        Credentials, TermStatus,
     },
+    time::USER_HZ,
     vdso::{vdso_vmo, VDSO_VMO_SIZE},
     vm::{perms::VmPerms, util::duplicate_frame, vmar::Vmar, vmo::VmoRightsOp},
 };
@@ -37,28 +39,39 @@ pub fn load_elf_to_vm(
     fs_resolver: &FsResolver,
     argv: Vec<CString>,
     envp: Vec<CString>,
+    credentials: &Credentials<ReadOp>,
 ) -> Result<ElfLoadInfo> {
     let parsed_elf = Elf::parse_elf(file_header)?;
 
     let ldso = lookup_and_parse_ldso(&parsed_elf, file_header, fs_resolver)?;
 
-    match init_and_map_vmos(process_vm, ldso, &parsed_elf, &elf_file) {
+    // All the mappings of a 32-bit program must be below `COMPAT_TASK_SIZE`.
+    let is_compat = parsed_elf.is_compat();
+    if is_compat {
+        process_vm.set_compat_layout();
+    }
+
+    match init_and_map_vmos(process_vm, ldso, &parsed_elf, &elf_file, credentials) {
         Ok((entry_point, mut aux_vec)) => {
             // Map and set vdso entry.
             // Since vdso does not require being mapped to any specific address,
             // vdso is mapped after the elf file, heap and stack are mapped.
-            if let Some(vdso_text_base) = map_vdso_to_vm(process_vm) {
-                aux_vec
-                    .set(AuxKey::AT_SYSINFO_EHDR, vdso_text_base as u64)
-                    .unwrap();
+            // The vdso is built for 64-bit programs, so it is not mapped for a 32-bit program.
+            if !is_compat {
+                if let Some(vdso_text_base) = map_vdso_to_vm(process_vm) {
+                    aux_vec
+                        .set(AuxKey::AT_SYSINFO_EHDR, vdso_text_base as u64)
+                        .unwrap();
+                }
             }
 
-            process_vm.map_and_write_init_stack(argv, envp, aux_vec)?;
+            process_vm.map_and_write_init_stack(argv, envp, aux_vec, is_compat)?;
 
             let user_stack_top = process_vm.user_stack_top();
             Ok(ElfLoadInfo {
                 entry_point,
                 user_stack_top,
+                is_compat,
             })
         }
         Err(err) => {
@@ -98,11 +111,14 @@ fn lookup_and_parse_ldso(
         inode.read_bytes_at(0, &mut *buf)?;
         Elf::parse_elf(&*buf)?
     };
+    if ldso_elf.is_compat() != elf.is_compat() {
+        return_errno_with_message!(Errno::ELIBBAD, "the class of ldso mismatches");
+    }
     Ok(Some((ldso_file, ldso_elf)))
 }
 
 fn load_ldso(root_vmar: &Vmar<Full>, ldso_file: &Dentry, ldso_elf: &Elf) -> Result<LdsoLoadInfo> {
-    let map_addr = map_segment_vmos(ldso_elf, root_vmar, ldso_file)?;
+    let map_addr = map_segment_vmos(ldso_elf, root_vmar, ldso_file, None)?;
     Ok(LdsoLoadInfo::new(
         ldso_elf.entry_point() + map_addr,
         map_addr,
@@ -114,6 +130,7 @@ fn init_and_map_vmos(
     ldso: Option<(Dentry, Elf)>,
     parsed_elf: &Elf,
     elf_file: &Dentry,
+    credentials: &Credentials<ReadOp>,
 ) -> Result<(Vaddr, AuxVec)> {
     let process_vmar = process_vm.lock_root_vmar();
     let root_vmar = process_vmar.get();
@@ -125,13 +142,19 @@ fn init_and_map_vmos(
         None
     };
 
-    let elf_map_addr = map_segment_vmos(parsed_elf, root_vmar, elf_file)?;
+    // Like Linux, a PIE that is loaded by ldso is placed at a separately randomized address,
+    // instead of at the mmap spaces.
+    let preferred_addr = ldso_load_info
+        .is_some()
+        .then(|| pie_base(parsed_elf.is_compat()));
+    let elf_map_addr = map_segment_vmos(parsed_elf, root_vmar, elf_file, preferred_addr)?;
+    record_code_and_data(process_vm, parsed_elf, elf_map_addr);
 
     let aux_vec = {
         let ldso_base = ldso_load_info
             .as_ref()
             .map(|load_info| load_info.base_addr());
-        init_aux_vec(parsed_elf, elf_map_addr, ldso_base)?
+        init_aux_vec(parsed_elf, elf_map_addr, ldso_base, credentials)?
     };
 
     let entry_point = if let Some(ldso_load_info) = ldso_load_info {
@@ -148,6 +171,39 @@ fn init_and_map_vmos(
     Ok((entry_point, aux_vec))
 }
 
+/// Records the addresses of the code and the data of the program in the `MmLayout`.
+///
+/// Like Linux, the code ends at the end of the last executable segment in the file, and the
+/// data spans from the start of the last segment to the end of all the segments in the file.
+fn record_code_and_data(process_vm: &ProcessVm, elf: &Elf, base_addr: Vaddr) {
+    let mut start_code = usize::MAX;
+    let mut end_code = 0;
+    let mut start_data = 0;
+    let mut end_data = 0;
+    for program_header in &elf.program_headers {
+        if !matches!(program_header.get_type(), Ok(program::Type::Load)) {
+            continue;
+        }
+        let start = program_header.virtual_addr as Vaddr;
+        let end = start + program_header.file_size as usize;
+        start_code = start_code.min(start);
+        start_data = start_data.max(start);
+        if program_header.flags.is_execute() {
+            end_code = end_code.max(end);
+        }
+        end_data = end_data.max(end);
+    }
+
+    // The code is empty if there are no executable segments.
+    let start_code = start_code.min(end_code);
+
+    let mut mm_layout = process_vm.mm_layout().lock();
+    mm_layout.start_code = start_code + base_addr;
+    mm_layout.end_code = end_code + base_addr;
+    mm_layout.start_data = start_data + base_addr;
+    mm_layout.end_data = end_data + base_addr;
+}
+
 pub struct LdsoLoadInfo {
     entry_point: Vaddr,
     base_addr: Vaddr,
@@ -173,13 +229,15 @@ impl LdsoLoadInfo {
 pub struct ElfLoadInfo {
     entry_point: Vaddr,
     user_stack_top: Vaddr,
+    is_compat: bool,
 }
 
 impl ElfLoadInfo {
-    pub fn new(entry_point: Vaddr, user_stack_top: Vaddr) -> Self {
+    pub fn new(entry_point: Vaddr, user_stack_top: Vaddr, is_compat: bool) -> Self {
         Self {
             entry_point,
             user_stack_top,
+            is_compat,
         }
     }
 
@@ -190,14 +248,26 @@ impl ElfLoadInfo {
     pub fn user_stack_top(&self) -> Vaddr {
         self.user_stack_top
     }
+
+    /// Returns whether the program is a 32-bit program.
+    pub fn is_compat(&self) -> bool {
+        self.is_compat
+    }
 }
 
 /// Inits VMO for each segment and then map segment to root vmar
-pub fn map_segment_vmos(elf: &Elf, root_vmar: &Vmar<Full>, elf_file: &Dentry) -> Result<Vaddr> {
+///
+/// A shared object is mapped at `preferred_addr` if it is given and the range is free.
+pub fn map_segment_vmos(
+    elf: &Elf,
+    root_vmar: &Vmar<Full>,
+    elf_file: &Dentry,
+    preferred_addr: Option<Vaddr>,
+) -> Result<Vaddr> {
     // all segments of the shared object must be mapped to a continuous vm range
     // to ensure the relative offset of each segment not changed.
     let base_addr = if elf.is_shared_object() {
-        base_map_addr(elf, root_vmar)?
+        base_map_addr(elf, root_vmar, preferred_addr)?
     } else {
         0
     };
@@ -213,7 +283,11 @@ pub fn map_segment_vmos(elf: &Elf, root_vmar: &Vmar<Full>, elf_file: &Dentry) ->
     Ok(base_addr)
 }
 
-fn base_map_addr(elf: &Elf, root_vmar: &Vmar<Full>) -> Result<Vaddr> {
+fn base_map_addr(
+    elf: &Elf,
+    root_vmar: &Vmar<Full>,
+    preferred_addr: Option<Vaddr>,
+) -> Result<Vaddr> {
     let elf_size = elf
         .program_headers
         .iter()
@@ -233,10 +307,18 @@ fn base_map_addr(elf: &Elf, root_vmar: &Vmar<Full>) -> Result<Vaddr> {
             "executable file does not has loadable sections",
         ))?;
     let map_size = elf_size.align_up(PAGE_SIZE);
-    let vmar_map_options = root_vmar
-        .new_map(map_size, VmPerms::empty())?
-        .handle_page_faults_around();
-    vmar_map_options.build()
+    let new_vmar_map_options = || -> Result<_> {
+        Ok(root_vmar
+            .new_map(map_size, VmPerms::empty())?
+            .handle_page_faults_around())
+    };
+
+    if let Some(preferred_addr) = preferred_addr
+        && let Ok(base_addr) = new_vmar_map_options()?.offset(preferred_addr).build()
+    {
+        return Ok(base_addr);
+    }
+    new_vmar_map_options()?.build()
 }
 
 /// Creates and map the corresponding segment VMO to `root_vmar`.
@@ -381,7 +463,12 @@ fn check_segment_align(program_header: &ProgramHeader64) -> Result<()> {
     Ok(())
 }
 
-pub fn init_aux_vec(elf: &Elf, elf_map_addr: Vaddr, ldso_base: Option<Vaddr>) -> Result<AuxVec> {
+pub fn init_aux_vec(
+    elf: &Elf,
+    elf_map_addr: Vaddr,
+    ldso_base: Option<Vaddr>,
+    credentials: &Credentials<ReadOp>,
+) -> Result<AuxVec> {
     let mut aux_vec = AuxVec::new();
     aux_vec.set(AuxKey::AT_PAGESZ, PAGE_SIZE as _)?;
     let ph_addr = if elf.is_shared_object() {
@@ -403,6 +490,24 @@ pub fn init_aux_vec(elf: &Elf, elf_map_addr: Vaddr, ldso_base: Option<Vaddr>) ->
     if let Some(ldso_base) = ldso_base {
         aux_vec.set(AuxKey::AT_BASE, ldso_base as u64)?;
     }
+    aux_vec.set(AuxKey::AT_FLAGS, 0)?;
+
+    aux_vec.set(AuxKey::AT_HWCAP, hwcap())?;
+    aux_vec.set(AuxKey::AT_CLKTCK, USER_HZ)?;
+
+    let ruid = u32::from(credentials.ruid());
+    let euid = u32::from(credentials.euid());
+    let rgid = u32::from(credentials.rgid());
+    let egid = u32::from(credentials.egid());
+    aux_vec.set(AuxKey::AT_UID, ruid as u64)?;
+    aux_vec.set(AuxKey::AT_EUID, euid as u64)?;
+    aux_vec.set(AuxKey::AT_GID, rgid as u64)?;
+    aux_vec.set(AuxKey::AT_EGID, egid as u64)?;
+    // The dynamic linker ignores the environment variables like `LD_PRELOAD` in the secure mode,
+    // so that they cannot be abused to control a set-user-ID or set-group-ID program.
+    let is_secure = ruid != euid || rgid != egid;
+    aux_vec.set(AuxKey::AT_SECURE, is_secure as u64)?;
+
     Ok(aux_vec)
 }
 
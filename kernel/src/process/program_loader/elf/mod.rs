@@ -3,4 +3,5 @@
 mod elf_file;
 mod load_elf;
 
+pub use elf_file::Elf;
 pub use load_elf::{load_elf_to_vm, ElfLoadInfo};
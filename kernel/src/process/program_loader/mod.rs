@@ -1,22 +1,30 @@
 // SPDX-License-Identifier: MPL-2.0
 
+pub mod binfmt_misc;
 pub mod elf;
 mod shebang;
 
+use aster_rights::ReadOp;
+
 use self::{
-    elf::{load_elf_to_vm, ElfLoadInfo},
+    binfmt_misc::BinfmtEntry,
+    elf::{load_elf_to_vm, Elf, ElfLoadInfo},
     shebang::parse_shebang_line,
 };
-use super::process_vm::ProcessVm;
+use super::{credentials::Credentials, process_vm::ProcessVm};
 use crate::{
     fs::{
         fs_resolver::{FsPath, FsResolver, AT_FDCWD},
-        path::Dentry,
+        path::{Dentry, PerMountFlags},
         utils::{InodeType, Permission},
     },
     prelude::*,
+    security::hooks,
 };
 
+/// The maximum number of the interpreters that can be followed when executing a file.
+pub const MAX_INTERPRETER_DEPTH: usize = 4;
+
 /// Represents an executable file that is ready to be loaded into memory and executed.
 ///
 /// This struct encapsulates the ELF file to be executed along with its header data,
@@ -29,19 +37,27 @@ pub struct ProgramToLoad {
 }
 
 impl ProgramToLoad {
-    /// Constructs a new `ProgramToLoad` from a file, handling shebang interpretation if needed.
+    /// Constructs a new `ProgramToLoad` from a file, handling the interpreters if needed.
     ///
-    /// About `recursion_limit`: recursion limit is used to limit th recursion depth of shebang executables.
-    /// If the interpreter(the program behind #!) of shebang executable is also a shebang,
-    /// then it will trigger recursion. We will try to setup root vmar for the interpreter.
-    /// I guess for most cases, setting the `recursion_limit` as 1 should be enough.
-    /// because the interpreter is usually an elf binary(e.g., /bin/bash)
+    /// If the file is a shebang script or is recognized by a handler of [`binfmt_misc`], the
+    /// interpreter is executed instead, with the path of the file in its arguments. Since the
+    /// interpreter may need an interpreter as well, at most [`MAX_INTERPRETER_DEPTH`]
+    /// interpreters are followed, as in Linux.
     pub fn build_from_file(
         elf_file: Dentry,
         fs_resolver: &FsResolver,
         argv: Vec<CString>,
         envp: Vec<CString>,
-        recursion_limit: usize,
+    ) -> Result<Self> {
+        Self::build_with_depth(elf_file, fs_resolver, argv, envp, 0)
+    }
+
+    fn build_with_depth(
+        elf_file: Dentry,
+        fs_resolver: &FsResolver,
+        argv: Vec<CString>,
+        envp: Vec<CString>,
+        depth: usize,
     ) -> Result<Self> {
         let inode = elf_file.inode();
         let file_header = {
@@ -50,36 +66,53 @@ impl ProgramToLoad {
             inode.read_bytes_at(0, &mut *file_header_buffer)?;
             file_header_buffer
         };
-        if let Some(mut new_argv) = parse_shebang_line(&*file_header)? {
-            if recursion_limit == 0 {
-                return_errno_with_message!(Errno::ELOOP, "the recursieve limit is reached");
-            }
-            new_argv.extend_from_slice(&argv);
+
+        let (interpreter, new_argv) = if let Some(mut new_argv) = parse_shebang_line(&*file_header)?
+        {
             let interpreter = {
                 let filename = new_argv[0].to_str()?.to_string();
                 let fs_path = FsPath::new(AT_FDCWD, &filename)?;
                 fs_resolver.lookup(&fs_path)?
             };
-            check_executable_file(&interpreter)?;
-            return Self::build_from_file(
-                interpreter,
-                fs_resolver,
-                new_argv,
+            // The path of the script replaces `argv[0]`.
+            new_argv.push(CString::new(elf_file.abs_path())?);
+            new_argv.extend(argv.into_iter().skip(1));
+            (interpreter, new_argv)
+        } else if let Some(entry) = find_binfmt_handler(&*file_header, &elf_file) {
+            let interpreter = entry.interpreter_file(fs_resolver)?;
+            let mut new_argv = vec![
+                CString::new(entry.interpreter())?,
+                CString::new(elf_file.abs_path())?,
+            ];
+            if entry.preserves_argv0() {
+                new_argv.extend(argv);
+            } else {
+                new_argv.extend(argv.into_iter().skip(1));
+            }
+            (interpreter, new_argv)
+        } else {
+            // Check the file before the old program is destroyed, so the error can be returned.
+            Elf::parse_elf(&*file_header)?;
+            return Ok(Self {
+                elf_file,
+                file_header,
+                argv,
                 envp,
-                recursion_limit - 1,
-            );
-        }
+            });
+        };
 
-        Ok(Self {
-            elf_file,
-            file_header,
-            argv,
-            envp,
-        })
+        if depth >= MAX_INTERPRETER_DEPTH {
+            return_errno_with_message!(Errno::ELOOP, "too many levels of interpreters");
+        }
+        check_executable_file(&interpreter)?;
+        Self::build_with_depth(interpreter, fs_resolver, new_argv, envp, depth + 1)
     }
 
     /// Loads the executable into the specified virtual memory space.
     ///
+    /// The credentials are those of the new program, which are reported to it in the auxiliary
+    /// vector.
+    ///
     /// Returns a tuple containing:
     /// 1. The absolute path of the loaded executable.
     /// 2. Information about the ELF loading process.
@@ -87,6 +120,7 @@ impl ProgramToLoad {
         self,
         process_vm: &ProcessVm,
         fs_resolver: &FsResolver,
+        credentials: &Credentials<ReadOp>,
     ) -> Result<(String, ElfLoadInfo)> {
         let abs_path = self.elf_file.abs_path();
         let elf_load_info = load_elf_to_vm(
@@ -96,12 +130,21 @@ impl ProgramToLoad {
             fs_resolver,
             self.argv,
             self.envp,
+            credentials,
         )?;
 
         Ok((abs_path, elf_load_info))
     }
 }
 
+/// Finds the handler of `binfmt_misc` for the file, which is never used for native executables.
+fn find_binfmt_handler(file_header: &[u8], file: &Dentry) -> Option<Arc<BinfmtEntry>> {
+    if Elf::parse_elf(file_header).is_ok() {
+        return None;
+    }
+    binfmt_misc::find_handler(file_header, &file.abs_path())
+}
+
 pub fn check_executable_file(dentry: &Dentry) -> Result<()> {
     if dentry.type_().is_directory() {
         return_errno_with_message!(Errno::EISDIR, "the file is a directory");
@@ -115,6 +158,10 @@ pub fn check_executable_file(dentry: &Dentry) -> Result<()> {
         return_errno_with_message!(Errno::EACCES, "the dentry is not a regular file");
     }
 
+    if dentry.mount_node().flags().contains(PerMountFlags::NOEXEC) {
+        return_errno_with_message!(Errno::EACCES, "the mount disallows execution");
+    }
+
     if dentry
         .inode()
         .check_permission(Permission::MAY_EXEC)
@@ -123,5 +170,7 @@ pub fn check_executable_file(dentry: &Dentry) -> Result<()> {
         return_errno_with_message!(Errno::EACCES, "the dentry is not executable");
     }
 
+    hooks::bprm_check(dentry)?;
+
     Ok(())
 }
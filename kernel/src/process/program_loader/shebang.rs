@@ -2,6 +2,9 @@
 
 use crate::prelude::*;
 
+/// The maximum length of a shebang line, which is the same as that of Linux.
+const MAX_SHEBANG_LEN: usize = 256;
+
 /// Try to parse a buffer as a shebang line.
 ///
 /// If the buffer starts with `#!` and its header is a valid shebang sequence,
@@ -10,24 +13,64 @@ use crate::prelude::*;
 /// If the buffer starts with `#!` but some error occurs while parsing the file,
 /// then `Err(_)` is returned.
 /// If the buffer does not start with `#!`, then `Ok(None)` is returned.
+///
+/// Like Linux, everything after the path of the interpreter is passed as one argument, e.g.,
+/// `#!/usr/bin/env -S python3 -u` has an interpreter of `/usr/bin/env` and an argument of
+/// `-S python3 -u`.
 pub fn parse_shebang_line(file_header_buffer: &[u8]) -> Result<Option<Vec<CString>>> {
-    if !file_header_buffer.starts_with(b"#!") || !file_header_buffer.contains(&b'\n') {
+    if !file_header_buffer.starts_with(b"#!") {
         // the file is not a shebang
         return Ok(None);
     }
-    let first_line_len = file_header_buffer.iter().position(|&c| c == b'\n').unwrap();
+
+    let buffer = &file_header_buffer[..file_header_buffer.len().min(MAX_SHEBANG_LEN)];
+    let (first_line, is_truncated) = match buffer.iter().position(|&c| c == b'\n') {
+        Some(first_line_len) => (&buffer[..first_line_len], false),
+        None => (buffer, true),
+    };
     // skip #!
-    let shebang_header = &file_header_buffer[2..first_line_len];
-    let mut shebang_argv = Vec::new();
-    for arg in shebang_header.split(|&c| c == b' ') {
-        let arg = CString::new(arg)?;
-        shebang_argv.push(arg);
+    let shebang_header = &first_line[2..];
+    // A NUL byte ends the line as well.
+    let shebang_header = shebang_header
+        .split(|&c| c == b'\0')
+        .next()
+        .unwrap_or_default();
+
+    let is_blank = |c: &u8| *c == b' ' || *c == b'\t';
+    let shebang_header = trim(shebang_header, is_blank);
+    let (interpreter, arg) = match shebang_header.iter().position(is_blank) {
+        Some(interpreter_len) => (
+            &shebang_header[..interpreter_len],
+            trim(&shebang_header[interpreter_len..], is_blank),
+        ),
+        None => (shebang_header, &[][..]),
+    };
+
+    if interpreter.is_empty() {
+        return_errno_with_message!(Errno::ENOEXEC, "no interpreter is specified");
     }
-    if shebang_argv.len() != 1 {
-        return_errno_with_message!(
-            Errno::EINVAL,
-            "One and only one interpreter program should be specified"
-        );
+    // If the line is too long, the path of the interpreter must be complete so that the wrong
+    // program cannot be executed. Only the argument may be truncated.
+    if is_truncated && arg.is_empty() {
+        return_errno_with_message!(Errno::ENOEXEC, "the interpreter path is too long");
+    }
+
+    let mut shebang_argv = vec![CString::new(interpreter)?];
+    if !arg.is_empty() {
+        shebang_argv.push(CString::new(arg)?);
     }
     Ok(Some(shebang_argv))
 }
+
+/// Removes the leading and trailing bytes that satisfy the predicate.
+fn trim(bytes: &[u8], predicate: impl Fn(&u8) -> bool) -> &[u8] {
+    let start = bytes
+        .iter()
+        .position(|c| !predicate(c))
+        .unwrap_or(bytes.len());
+    let end = bytes
+        .iter()
+        .rposition(|c| !predicate(c))
+        .map_or(start, |pos| pos + 1);
+    &bytes[start..end]
+}
@@ -0,0 +1,49 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The permission checks of the operations that inspect or modify another process, as Linux
+//! checks them for attaching `ptrace` (i.e., `ptrace_may_access`).
+//!
+//! Ptrace is not supported yet, but the same checks guard the system calls that access the
+//! memory or the resources of another process (e.g., `process_vm_readv`).
+
+use super::{posix_thread::AsPosixThread, Process};
+use crate::{prelude::*, process::credentials::capabilities::CapSet};
+
+/// Checks whether the current thread may access the process as a tracer.
+///
+/// The access is allowed if the process is the current process, if the real user and group of
+/// the current thread match all the (real, effective and saved) users and groups of the
+/// process, or if the current thread has `CAP_SYS_PTRACE`. Otherwise, it fails with `EPERM`.
+pub fn check_ptrace_access(target: &Process, ctx: &Context) -> Result<()> {
+    if core::ptr::eq(target, ctx.process) {
+        return Ok(());
+    }
+
+    let credentials = ctx.posix_thread.credentials();
+    if credentials.effective_capset().contains(CapSet::SYS_PTRACE) {
+        return Ok(());
+    }
+
+    let main_thread = target.main_thread();
+    let target_credentials = main_thread.as_posix_thread().unwrap().credentials();
+    let (uid, gid) = (credentials.ruid(), credentials.rgid());
+    let is_same_user = [
+        target_credentials.ruid(),
+        target_credentials.euid(),
+        target_credentials.suid(),
+    ]
+    .iter()
+    .all(|target_uid| *target_uid == uid);
+    let is_same_group = [
+        target_credentials.rgid(),
+        target_credentials.egid(),
+        target_credentials.sgid(),
+    ]
+    .iter()
+    .all(|target_gid| *target_gid == gid);
+    if is_same_user && is_same_group {
+        return Ok(());
+    }
+
+    return_errno_with_message!(Errno::EPERM, "the process cannot be accessed");
+}
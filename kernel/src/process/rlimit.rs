@@ -1,5 +1,4 @@
 // SPDX-License-Identifier: MPL-2.0
-// FIXME: The resource limits should be respected by the corresponding subsystems of the kernel.
 
 #![expect(non_camel_case_types)]
 
@@ -14,7 +13,8 @@ use crate::prelude::*;
 // Constants for the boot-time rlimit defaults
 // See https://github.com/torvalds/linux/blob/fac04efc5c793dccbd07e2d59af9f90b7fc0dca4/include/asm-generic/resource.h#L11
 const RLIM_INFINITY: u64 = u64::MAX;
-const INIT_RLIMIT_NPROC: u64 = 0;
+// Linux sets the limit to half of the maximum number of threads during boot.
+const INIT_RLIMIT_NPROC: u64 = 16384;
 const INIT_RLIMIT_NICE: u64 = 0;
 const INIT_RLIMIT_SIGPENDING: u64 = 0;
 const INIT_RLIMIT_RTPRIO: u64 = 0;
@@ -26,6 +26,22 @@ const INIT_RLIMIT_MEMLOCK: u64 = 8 * 1024 * 1024;
 // https://github.com/torvalds/linux/blob/fac04efc5c793dccbd07e2d59af9f90b7fc0dca4/include/uapi/linux/mqueue.h#L26
 const INIT_RLIMIT_MSGQUEUE: u64 = 819200;
 
+/// The maximum value of `RLIMIT_NOFILE`, which is `/proc/sys/fs/nr_open`.
+///
+/// The default value is the same as that in Linux.
+static NR_OPEN: AtomicU64 = AtomicU64::new(1024 * 1024);
+
+/// Returns the maximum value of `RLIMIT_NOFILE`.
+pub fn nr_open() -> u64 {
+    NR_OPEN.load(Ordering::Relaxed)
+}
+
+/// Sets the maximum value of `RLIMIT_NOFILE`, which does not affect the current limits.
+pub fn set_nr_open(nr_open: u64) {
+    NR_OPEN.store(nr_open, Ordering::Relaxed);
+}
+
+#[derive(Clone)]
 pub struct ResourceLimits {
     rlimits: [RLimit64; RLIMIT_COUNT],
 }
@@ -160,6 +176,13 @@ impl RLimit64 {
     }
 }
 
+impl Clone for RLimit64 {
+    fn clone(&self) -> Self {
+        let (cur, max) = self.get_cur_and_max();
+        Self::new(cur, max).unwrap()
+    }
+}
+
 impl Default for RLimit64 {
     fn default() -> Self {
         Self {
@@ -58,6 +58,31 @@ impl siginfo_t {
         // let siginfo = *self;
         read_union_fields!(self.siginfo_fields.sigfault.addr)
     }
+
+    /// Sets the fields of a signal generated by a POSIX timer.
+    pub fn set_si_timer(&mut self, timer_id: i32, overrun: i32, value: sigval_t) {
+        self.siginfo_fields.common.first.timer = siginfo_timer_t {
+            timerid: timer_id,
+            overrun,
+        };
+        self.siginfo_fields.common.second.value = value;
+    }
+
+    /// Sets the fields of a real-time signal that carries a value (e.g., a signal generated by
+    /// a message queue notification).
+    pub fn set_si_rt(&mut self, pid: Pid, uid: Uid, value: sigval_t) {
+        self.siginfo_fields.common.first.piduid = siginfo_piduid_t { pid, uid };
+        self.siginfo_fields.common.second.value = value;
+    }
+
+    /// Sets the fields of a `SIGSYS` signal that reports a system call.
+    pub fn set_si_sys(&mut self, call_addr: Vaddr, syscall: i32, arch: u32) {
+        self.siginfo_fields.sigsys = siginfo_sigsys_t {
+            call_addr,
+            syscall,
+            arch,
+        };
+    }
 }
 
 #[derive(Clone, Copy, Pod)]
@@ -66,6 +91,7 @@ union siginfo_fields_t {
     bytes: [u8; 128 - mem::size_of::<i32>() * 4],
     common: siginfo_common_t,
     sigfault: siginfo_sigfault_t,
+    sigsys: siginfo_sigsys_t,
 }
 
 impl siginfo_fields_t {
@@ -78,7 +104,7 @@ impl siginfo_fields_t {
 
 #[derive(Clone, Copy, Pod)]
 #[repr(C)]
-union siginfo_common_t {
+struct siginfo_common_t {
     first: siginfo_common_first_t,
     second: siginfo_common_second_t,
 }
@@ -119,6 +145,13 @@ pub union sigval_t {
 }
 
 impl sigval_t {
+    pub fn from_int(sigval_int: i32) -> Self {
+        // Zero the upper bytes of the pointer.
+        let mut value = Self { sigval_ptr: 0 };
+        value.sigval_int = sigval_int;
+        value
+    }
+
     pub fn read_int(&self) -> i32 {
         read_union_fields!(self.sigval_int)
     }
@@ -144,6 +177,14 @@ struct siginfo_sigfault_t {
     first: siginfo_sigfault_first_t,
 }
 
+#[derive(Clone, Copy, Pod)]
+#[repr(C)]
+struct siginfo_sigsys_t {
+    call_addr: Vaddr, // *const c_void
+    syscall: i32,
+    arch: u32,
+}
+
 #[derive(Clone, Copy, Pod)]
 #[repr(C)]
 union siginfo_sigfault_first_t {
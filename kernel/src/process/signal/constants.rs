@@ -105,3 +105,6 @@ pub const CLD_DUMPED: i32 = 3;
 pub const CLD_TRAPPED: i32 = 4;
 pub const CLD_STOPPED: i32 = 5;
 pub const CLD_CONTINUED: i32 = 6;
+
+pub const SYS_SECCOMP: i32 = 1;
+pub const SYS_USER_DISPATCH: i32 = 2;
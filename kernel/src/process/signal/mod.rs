@@ -176,6 +176,10 @@ pub fn handle_user_signal(
         mask += sig_num;
     }
 
+    // Restart the restartable sequence before the user context is saved, so that the thread
+    // goes to the abort handler after the signal handler returns.
+    ctx.thread_local.rseq().handle_signal(ctx, user_ctx)?;
+
     // Block signals in sigmask when running signal handler
     let old_mask = ctx.posix_thread.sig_mask().load(Ordering::Relaxed);
     ctx.posix_thread
@@ -2,6 +2,9 @@
 
 pub mod fault;
 pub mod kernel;
+pub mod mqueue;
+pub mod syscall;
+pub mod timer;
 pub mod user;
 
 use core::{any::Any, fmt::Debug};
@@ -0,0 +1,55 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::Signal;
+use crate::process::{
+    signal::{
+        c_types::{siginfo_t, sigval_t},
+        constants::SI_MESGQ,
+        sig_num::SigNum,
+    },
+    Pid, Uid,
+};
+
+/// A signal generated by the notification of a POSIX message queue.
+#[derive(Clone, Copy)]
+pub struct MqNotifySignal {
+    num: SigNum,
+    value: sigval_t,
+    /// The PID of the process that sends the message
+    pid: Pid,
+    /// The real UID of the process that sends the message
+    uid: Uid,
+}
+
+impl MqNotifySignal {
+    pub fn new(num: SigNum, value: sigval_t, pid: Pid, uid: Uid) -> Self {
+        Self {
+            num,
+            value,
+            pid,
+            uid,
+        }
+    }
+}
+
+impl Signal for MqNotifySignal {
+    fn num(&self) -> SigNum {
+        self.num
+    }
+
+    fn to_info(&self) -> siginfo_t {
+        let mut info = siginfo_t::new(self.num, SI_MESGQ);
+        info.set_si_rt(self.pid, self.uid, self.value);
+        info
+    }
+}
+
+impl core::fmt::Debug for MqNotifySignal {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("MqNotifySignal")
+            .field("num", &self.num)
+            .field("pid", &self.pid)
+            .field("uid", &self.uid)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,47 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::Signal;
+use crate::{
+    prelude::*,
+    process::signal::{c_types::siginfo_t, constants::SIGSYS, sig_num::SigNum},
+};
+
+/// A `SIGSYS` signal that reports a system call that is not made, which is sent by seccomp
+/// or syscall user dispatch.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SyscallSignal {
+    code: i32,
+    errno: i32,
+    call_addr: Vaddr,
+    syscall: i32,
+    arch: u32,
+}
+
+impl SyscallSignal {
+    /// Creates a signal for the system call `syscall` of the architecture `arch`, which is
+    /// made by the instruction before `call_addr`.
+    ///
+    /// The `errno` field carries the data of `SECCOMP_RET_TRAP`, or zero otherwise.
+    pub fn new(code: i32, errno: i32, call_addr: Vaddr, syscall: i32, arch: u32) -> Self {
+        Self {
+            code,
+            errno,
+            call_addr,
+            syscall,
+            arch,
+        }
+    }
+}
+
+impl Signal for SyscallSignal {
+    fn num(&self) -> SigNum {
+        SIGSYS
+    }
+
+    fn to_info(&self) -> siginfo_t {
+        let mut info = siginfo_t::new(SIGSYS, self.code);
+        info.si_errno = self.errno;
+        info.set_si_sys(self.call_addr, self.syscall, self.arch);
+        info
+    }
+}
@@ -0,0 +1,67 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::fmt::Debug;
+
+use spin::Once;
+
+use super::Signal;
+use crate::{
+    prelude::*,
+    process::{
+        signal::{
+            c_types::{siginfo_t, sigval_t},
+            constants::SI_TIMER,
+            sig_num::SigNum,
+        },
+        PosixTimer,
+    },
+};
+
+/// A signal generated by the expiration of a POSIX timer.
+pub struct TimerSignal {
+    num: SigNum,
+    value: sigval_t,
+    timer: Arc<PosixTimer>,
+    /// The overrun count, which is collected from the timer when the signal is delivered.
+    overrun: Once<u32>,
+}
+
+impl TimerSignal {
+    pub fn new(num: SigNum, value: sigval_t, timer: Arc<PosixTimer>) -> Self {
+        Self {
+            num,
+            value,
+            timer,
+            overrun: Once::new(),
+        }
+    }
+}
+
+impl Signal for TimerSignal {
+    fn num(&self) -> SigNum {
+        self.num
+    }
+
+    fn to_info(&self) -> siginfo_t {
+        let overrun = *self.overrun.call_once(|| self.timer.collect_overrun());
+
+        let mut info = siginfo_t::new(self.num, SI_TIMER);
+        info.set_si_timer(self.timer.id() as i32, overrun as i32, self.value);
+        info
+    }
+}
+
+impl Drop for TimerSignal {
+    fn drop(&mut self) {
+        self.timer.clear_signal_pending();
+    }
+}
+
+impl Debug for TimerSignal {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("TimerSignal")
+            .field("num", &self.num)
+            .field("timer_id", &self.timer.id())
+            .finish_non_exhaustive()
+    }
+}
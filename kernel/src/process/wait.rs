@@ -93,6 +93,7 @@ pub fn wait_child_exit(
 fn reap_zombie_child(process: &Process, pid: Pid) -> ExitCode {
     let child_process = process.children().lock().remove(&pid).unwrap();
     assert!(child_process.status().is_zombie());
+    accumulate_children_cpu_time(process, &child_process);
     for task in child_process.tasks().lock().as_slice() {
         thread_table::remove_thread(task.as_posix_thread().unwrap().tid());
     }
@@ -126,3 +127,18 @@ fn reap_zombie_child(process: &Process, pid: Pid) -> ExitCode {
     process_table_mut.remove(child_process.pid());
     child_process.status().exit_code()
 }
+
+/// Adds the CPU time of the reaped child and its reaped descendants to the process.
+fn accumulate_children_cpu_time(process: &Process, child_process: &Process) {
+    let children_clock = process.children_prof_clock();
+    let (child_clock, grandchildren_clock) = (
+        child_process.prof_clock(),
+        child_process.children_prof_clock(),
+    );
+    children_clock.user_clock().add_time(
+        child_clock.user_clock().read_time() + grandchildren_clock.user_clock().read_time(),
+    );
+    children_clock.kernel_clock().add_time(
+        child_clock.kernel_clock().read_time() + grandchildren_clock.kernel_clock().read_time(),
+    );
+}
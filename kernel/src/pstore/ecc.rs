@@ -0,0 +1,336 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The Reed-Solomon codes that protect the data in the persistent RAM.
+//!
+//! The codes are the same as those used by Linux: the symbols are 8 bits, the field
+//! generator polynomial is `0x11d`, the first consecutive root is 0, and the primitive
+//! element is 1. Each block is encoded as a shortened codeword of the full 255 symbols, so
+//! that the parity of the blocks is compatible with Linux.
+//!
+//! The implementation follows the encoder and decoder of Phil Karn, which are also the
+//! basis of the Linux implementation.
+//!
+//! Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/lib/reed_solomon/decode_rs.c>
+
+use crate::prelude::*;
+
+/// The number of the symbols in a full codeword.
+const NN: usize = 255;
+/// The field generator polynomial.
+const GF_POLY: usize = 0x11d;
+/// The logarithm of zero, which does not exist, in the index form.
+const A0: usize = NN;
+
+/// A Reed-Solomon code with a given number of parity symbols.
+pub(super) struct ReedSolomon {
+    /// The antilogarithm table (from the index form to the polynomial form)
+    alpha_to: [u8; NN + 1],
+    /// The logarithm table (from the polynomial form to the index form)
+    index_of: [usize; NN + 1],
+    /// The generator polynomial in the index form
+    genpoly: Vec<usize>,
+    nroots: usize,
+}
+
+impl ReedSolomon {
+    /// Creates a code with `nroots` parity symbols.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `nroots` is zero or too large for any data.
+    pub(super) fn new(nroots: usize) -> Self {
+        assert!(nroots > 0 && nroots < NN);
+
+        let mut alpha_to = [0u8; NN + 1];
+        let mut index_of = [0usize; NN + 1];
+        let mut sr = 1;
+        for (i, alpha) in alpha_to.iter_mut().take(NN).enumerate() {
+            index_of[sr] = i;
+            *alpha = sr as u8;
+            sr <<= 1;
+            if sr > NN {
+                sr ^= GF_POLY;
+            }
+        }
+        index_of[0] = A0;
+        alpha_to[NN] = 0;
+
+        // Build the generator polynomial, whose roots are the consecutive powers of the
+        // primitive element, in the polynomial form first.
+        let mut genpoly = vec![0usize; nroots + 1];
+        genpoly[0] = 1;
+        for i in 0..nroots {
+            genpoly[i + 1] = 1;
+            for j in (1..=i).rev() {
+                genpoly[j] = if genpoly[j] != 0 {
+                    genpoly[j - 1] ^ alpha_to[modnn(index_of[genpoly[j]] + i)] as usize
+                } else {
+                    genpoly[j - 1]
+                };
+            }
+            genpoly[0] = alpha_to[modnn(index_of[genpoly[0]] + i)] as usize;
+        }
+        for coefficient in genpoly.iter_mut() {
+            *coefficient = index_of[*coefficient];
+        }
+
+        Self {
+            alpha_to,
+            index_of,
+            genpoly,
+            nroots,
+        }
+    }
+
+    /// Returns the number of the parity symbols.
+    pub(super) fn nroots(&self) -> usize {
+        self.nroots
+    }
+
+    /// Computes the parity of the data.
+    ///
+    /// The length of `parity` must be the number of the parity symbols.
+    pub(super) fn encode(&self, data: &[u8], parity: &mut [u8]) {
+        debug_assert!(data.len() + self.nroots <= NN);
+        debug_assert_eq!(parity.len(), self.nroots);
+
+        let nroots = self.nroots;
+        parity.fill(0);
+        for byte in data.iter() {
+            let feedback = self.index_of[(byte ^ parity[0]) as usize];
+            if feedback != A0 {
+                for j in 1..nroots {
+                    parity[j] ^= self.alpha_to[modnn(feedback + self.genpoly[nroots - j])];
+                }
+            }
+            parity.copy_within(1.., 0);
+            parity[nroots - 1] = if feedback != A0 {
+                self.alpha_to[modnn(feedback + self.genpoly[0])]
+            } else {
+                0
+            };
+        }
+    }
+
+    /// Corrects the errors in the data with the parity.
+    ///
+    /// This method returns the number of the corrected symbols, or `None` if the errors
+    /// cannot be corrected.
+    pub(super) fn decode(&self, data: &mut [u8], parity: &mut [u8]) -> Option<usize> {
+        debug_assert!(data.len() + self.nroots <= NN);
+        debug_assert_eq!(parity.len(), self.nroots);
+
+        let (alpha_to, index_of) = (&self.alpha_to, &self.index_of);
+        let nroots = self.nroots;
+        let pad = NN - nroots - data.len();
+
+        // Compute the syndromes by evaluating the codeword at the roots.
+        let mut syndromes = vec![0u8; nroots];
+        for byte in data.iter().chain(parity.iter()) {
+            for (i, syndrome) in syndromes.iter_mut().enumerate() {
+                *syndrome = if *syndrome == 0 {
+                    *byte
+                } else {
+                    byte ^ alpha_to[modnn(index_of[*syndrome as usize] + i)]
+                };
+            }
+        }
+        if syndromes.iter().all(|syndrome| *syndrome == 0) {
+            return Some(0);
+        }
+        let s: Vec<usize> = syndromes
+            .iter()
+            .map(|syndrome| index_of[*syndrome as usize])
+            .collect();
+
+        // Find the error locator polynomial with the Berlekamp-Massey algorithm.
+        let mut lambda = vec![0usize; nroots + 1];
+        lambda[0] = 1;
+        let mut b: Vec<usize> = lambda.iter().map(|coef| index_of[*coef]).collect();
+        let mut t = vec![0usize; nroots + 1];
+        let mut el = 0;
+        for r in 1..=nroots {
+            let mut discr = 0;
+            for i in 0..r {
+                if lambda[i] != 0 && s[r - i - 1] != A0 {
+                    discr ^= alpha_to[modnn(index_of[lambda[i]] + s[r - i - 1])] as usize;
+                }
+            }
+            let discr = index_of[discr];
+
+            if discr == A0 {
+                b.copy_within(0..nroots, 1);
+                b[0] = A0;
+                continue;
+            }
+
+            t[0] = lambda[0];
+            for i in 0..nroots {
+                t[i + 1] = if b[i] != A0 {
+                    lambda[i + 1] ^ alpha_to[modnn(discr + b[i])] as usize
+                } else {
+                    lambda[i + 1]
+                };
+            }
+            if 2 * el < r {
+                el = r - el;
+                for (b, lambda) in b.iter_mut().zip(lambda.iter()) {
+                    *b = if *lambda == 0 {
+                        A0
+                    } else {
+                        modnn(index_of[*lambda] + NN - discr)
+                    };
+                }
+            } else {
+                b.copy_within(0..nroots, 1);
+                b[0] = A0;
+            }
+            lambda.copy_from_slice(&t);
+        }
+
+        let mut deg_lambda = 0;
+        for (i, coef) in lambda.iter_mut().enumerate() {
+            *coef = index_of[*coef];
+            if *coef != A0 {
+                deg_lambda = i;
+            }
+        }
+        if deg_lambda == 0 {
+            return None;
+        }
+
+        // Find the roots of the error locator polynomial with the Chien search.
+        let mut reg = lambda.clone();
+        let mut roots = Vec::with_capacity(deg_lambda);
+        let mut locations = Vec::with_capacity(deg_lambda);
+        for i in 1..=NN {
+            let mut q = 1;
+            for (j, reg) in reg.iter_mut().enumerate().take(deg_lambda + 1).skip(1) {
+                if *reg != A0 {
+                    *reg = modnn(*reg + j);
+                    q ^= alpha_to[*reg];
+                }
+            }
+            if q != 0 {
+                continue;
+            }
+
+            // The error is in the padding, which is impossible for a shortened codeword.
+            let location = i - 1;
+            if location < pad {
+                return None;
+            }
+            roots.push(i);
+            locations.push(location);
+            if roots.len() == deg_lambda {
+                break;
+            }
+        }
+        if roots.len() != deg_lambda {
+            return None;
+        }
+
+        // Compute the error evaluator polynomial.
+        let deg_omega = deg_lambda - 1;
+        let mut omega = vec![0usize; deg_omega + 1];
+        for (i, omega) in omega.iter_mut().enumerate() {
+            let mut tmp = 0;
+            for j in 0..=i {
+                if s[i - j] != A0 && lambda[j] != A0 {
+                    tmp ^= alpha_to[modnn(s[i - j] + lambda[j])] as usize;
+                }
+            }
+            *omega = index_of[tmp];
+        }
+
+        // Compute the error values with the Forney algorithm, and correct the errors.
+        for (root, location) in roots.iter().zip(locations.iter()) {
+            let mut num1 = 0;
+            for (i, omega) in omega.iter().enumerate() {
+                if *omega != A0 {
+                    num1 ^= alpha_to[modnn(omega + i * root)] as usize;
+                }
+            }
+            if num1 == 0 {
+                continue;
+            }
+            let num2 = alpha_to[modnn(NN - root)] as usize;
+
+            let mut den = 0;
+            for i in (0..=deg_lambda.min(nroots - 1) & !1).step_by(2) {
+                if lambda[i + 1] != A0 {
+                    den ^= alpha_to[modnn(lambda[i + 1] + i * root)] as usize;
+                }
+            }
+            if den == 0 {
+                return None;
+            }
+
+            let correction = alpha_to[modnn(index_of[num1] + index_of[num2] + NN - index_of[den])];
+            let position = location - pad;
+            if position < data.len() {
+                data[position] ^= correction;
+            } else {
+                parity[position - data.len()] ^= correction;
+            }
+        }
+
+        Some(roots.len())
+    }
+}
+
+fn modnn(x: usize) -> usize {
+    x % NN
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    fn encoded(rs: &ReedSolomon, data: &[u8]) -> Vec<u8> {
+        let mut parity = vec![0u8; rs.nroots()];
+        rs.encode(data, &mut parity);
+        parity
+    }
+
+    #[ktest]
+    fn no_errors() {
+        let rs = ReedSolomon::new(16);
+        let mut data: Vec<u8> = (0..128).map(|i| (i * 7) as u8).collect();
+        let mut parity = encoded(&rs, &data);
+        assert_eq!(rs.decode(&mut data, &mut parity), Some(0));
+    }
+
+    #[ktest]
+    fn correct_errors() {
+        let rs = ReedSolomon::new(16);
+        let original: Vec<u8> = (0..128).map(|i| (i * 13 + 5) as u8).collect();
+        let original_parity = encoded(&rs, &original);
+
+        let mut data = original.clone();
+        let mut parity = original_parity.clone();
+        for i in [0, 17, 64, 100, 127] {
+            data[i] ^= 0x5a;
+        }
+        parity[3] ^= 0xff;
+        assert_eq!(rs.decode(&mut data, &mut parity), Some(6));
+        assert_eq!(data, original);
+        assert_eq!(parity, original_parity);
+    }
+
+    #[ktest]
+    fn too_many_errors() {
+        let rs = ReedSolomon::new(4);
+        let original = b"persistent ram".to_vec();
+        let mut parity = encoded(&rs, &original);
+
+        let mut data = original.clone();
+        for byte in data.iter_mut().take(5) {
+            *byte = !*byte;
+        }
+        let result = rs.decode(&mut data, &mut parity);
+        assert!(result.is_none() || data != original);
+    }
+}
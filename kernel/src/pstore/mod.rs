@@ -0,0 +1,369 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Persistent storage of the kernel logs (pstore) in the ramoops region.
+//!
+//! Like Linux, the ramoops region (see [`ostd::arch::ramoops`]) is divided into zones. The
+//! dmesg zones store the logs dumped on oopses and panics, and the console zone keeps the
+//! latest console output. Since there is no separate kernel log buffer, the dumped logs are
+//! the tail of the console output. The ftrace and pmsg zones are reserved but not used.
+//!
+//! The records found in the zones at boot are written in the previous boots, which are
+//! exported by the pstore filesystem (see [`crate::fs::pstore`]). Removing a dmesg record
+//! from the filesystem erases it from the RAM, so that its zone can be reused.
+//!
+//! Reference: <https://docs.kernel.org/admin-guide/ramoops.html>
+
+mod ecc;
+mod ram;
+
+use alloc::format;
+use core::{
+    fmt::Display,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use ostd::arch::ramoops::ramoops_region;
+use spin::Once;
+
+use self::{
+    ecc::ReedSolomon,
+    ram::{OldContent, RamZone, ECC_BLOCK_SIZE},
+};
+use crate::{prelude::*, time::clocks::RealTimeClock};
+
+/// The prefix of the header of the dmesg records, which is followed by the time.
+const DMESG_HEADER_PREFIX: &[u8] = b"====";
+
+/// The kind of a record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordKind {
+    Dmesg,
+    Console,
+}
+
+/// A record written in the previous boots.
+#[derive(Debug)]
+pub struct Record {
+    kind: RecordKind,
+    id: usize,
+    time: Duration,
+    is_compressed: bool,
+    data: Vec<u8>,
+}
+
+impl Record {
+    /// Returns the name of the record, which is the file name in the pstore filesystem.
+    pub fn name(&self) -> String {
+        let kind = match self.kind {
+            RecordKind::Dmesg => "dmesg",
+            RecordKind::Console => "console",
+        };
+        let suffix = if self.is_compressed { ".enc.z" } else { "" };
+        format!("{}-ramoops-{}{}", kind, self.id, suffix)
+    }
+
+    /// Returns the time when the record was written.
+    ///
+    /// The time of console records is unknown, so it is the time of the current boot.
+    pub fn time(&self) -> Duration {
+        self.time
+    }
+
+    /// Returns the content of the record.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    fn new_dmesg(id: usize, old: OldContent) -> Self {
+        let OldContent {
+            mut data,
+            ecc_stats,
+        } = old;
+
+        let (time, is_compressed) = match parse_dmesg_header(&data) {
+            Some((time, is_compressed, header_len)) => {
+                data.drain(..header_len);
+                (time, is_compressed)
+            }
+            None => (Duration::ZERO, false),
+        };
+        if !is_compressed {
+            append_ecc_stats(&mut data, ecc_stats);
+        }
+
+        Self {
+            kind: RecordKind::Dmesg,
+            id,
+            time,
+            is_compressed,
+            data,
+        }
+    }
+
+    fn new_console(old: OldContent, boot_time: Duration) -> Self {
+        let OldContent {
+            mut data,
+            ecc_stats,
+        } = old;
+        append_ecc_stats(&mut data, ecc_stats);
+
+        Self {
+            kind: RecordKind::Console,
+            id: 0,
+            time: boot_time,
+            is_compressed: false,
+            data,
+        }
+    }
+}
+
+/// The reason of dumping the kernel logs.
+#[derive(Debug, Clone, Copy)]
+pub enum DumpReason {
+    Oops,
+    Panic,
+}
+
+struct Ramoops {
+    dmesg_zones: Vec<DmesgZone>,
+    console_zone: Option<RamZone>,
+    records: Mutex<Vec<Arc<Record>>>,
+}
+
+struct DmesgZone {
+    zone: RamZone,
+    /// Whether the zone contains a record written in the previous boots
+    has_old_record: AtomicBool,
+    /// Whether the zone contains a record written in the current boot
+    has_new_record: AtomicBool,
+}
+
+static RAMOOPS: Once<Ramoops> = Once::new();
+
+/// The number of the dumps in the current boot.
+static DUMP_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Whether the kernel is panicking.
+///
+/// After the kernel panics, the console output is dropped if the console zone is being
+/// written, to avoid deadlocks.
+static IS_PANICKING: AtomicBool = AtomicBool::new(false);
+
+pub(super) fn init() {
+    let Some(region) = ramoops_region() else {
+        return;
+    };
+
+    // Like Linux, the sizes of the records are rounded down to powers of two.
+    let record_size = round_down_pow_of_two(region.record_size);
+    let console_size = round_down_pow_of_two(region.console_size);
+    let ftrace_size = round_down_pow_of_two(region.ftrace_size);
+    let pmsg_size = round_down_pow_of_two(region.pmsg_size);
+    let Some(dump_size) = region
+        .mem
+        .length()
+        .checked_sub(console_size + ftrace_size + pmsg_size)
+    else {
+        warn!("The ramoops region is too small for the records");
+        return;
+    };
+
+    let ecc = match region.ecc_size {
+        0 => None,
+        ecc_size if ecc_size + ECC_BLOCK_SIZE < 255 => Some(Arc::new(ReedSolomon::new(ecc_size))),
+        _ => {
+            warn!("The ECC size of the ramoops region is too large");
+            return;
+        }
+    };
+
+    let mut offset = 0;
+    let mut dmesg_zones = Vec::new();
+    if record_size > 0 && dump_size >= record_size {
+        let zone_size = dump_size / (dump_size / record_size);
+        for _ in 0..dump_size / record_size {
+            let zone = RamZone::new(region.mem.clone(), offset, zone_size, ecc.clone());
+            dmesg_zones.extend(zone.map(|zone| DmesgZone {
+                zone,
+                has_old_record: AtomicBool::new(false),
+                has_new_record: AtomicBool::new(false),
+            }));
+            offset += zone_size;
+        }
+    }
+    let console_zone = RamZone::new(region.mem.clone(), offset, console_size, ecc);
+
+    let boot_time = RealTimeClock::get().read_time();
+    let mut records = Vec::new();
+    for (id, dmesg_zone) in dmesg_zones.iter().enumerate() {
+        if let Some(old) = dmesg_zone.zone.load() {
+            dmesg_zone.has_old_record.store(true, Ordering::Relaxed);
+            records.push(Arc::new(Record::new_dmesg(id, old)));
+        }
+    }
+    if let Some(console_zone) = console_zone.as_ref() {
+        if let Some(old) = console_zone.load() {
+            records.push(Arc::new(Record::new_console(old, boot_time)));
+        }
+        // The console zone is for the output of the current boot.
+        console_zone.zap();
+    }
+
+    RAMOOPS.call_once(|| Ramoops {
+        dmesg_zones,
+        console_zone,
+        records: Mutex::new(records),
+    });
+    aster_logger::register_print_hook(write_console);
+}
+
+/// Returns the records written in the previous boots.
+pub fn records() -> Vec<Arc<Record>> {
+    RAMOOPS
+        .get()
+        .map(|ramoops| ramoops.records.lock().clone())
+        .unwrap_or_default()
+}
+
+/// Erases the record from the persistent storage.
+pub fn erase(record: &Arc<Record>) {
+    let Some(ramoops) = RAMOOPS.get() else {
+        return;
+    };
+    ramoops
+        .records
+        .lock()
+        .retain(|other| !Arc::ptr_eq(other, record));
+
+    // The console zone has been reused at boot, so only the dmesg zones need to be reset.
+    if record.kind != RecordKind::Dmesg {
+        return;
+    }
+    let dmesg_zone = &ramoops.dmesg_zones[record.id];
+    if dmesg_zone.has_old_record.swap(false, Ordering::Relaxed)
+        && !dmesg_zone.has_new_record.load(Ordering::Relaxed)
+    {
+        dmesg_zone.zone.zap();
+    }
+}
+
+/// Dumps the kernel logs to the persistent storage because of an oops or a panic.
+///
+/// The logs are the tail of the console output, which should contain the `message` of the
+/// oops or the panic. If the console output is not kept, the logs are the `message`.
+pub fn dump(reason: DumpReason, message: &dyn Display) {
+    if matches!(reason, DumpReason::Panic) {
+        IS_PANICKING.store(true, Ordering::Relaxed);
+    }
+
+    let Some(ramoops) = RAMOOPS.get() else {
+        return;
+    };
+    if ramoops.dmesg_zones.is_empty() {
+        return;
+    }
+
+    // Use the free zones first, and then overwrite the zones in turn.
+    let count = DUMP_COUNT.fetch_add(1, Ordering::Relaxed) + 1;
+    let dmesg_zone = ramoops
+        .dmesg_zones
+        .iter()
+        .find(|dmesg_zone| {
+            !dmesg_zone.has_old_record.load(Ordering::Relaxed)
+                && !dmesg_zone.has_new_record.load(Ordering::Relaxed)
+        })
+        .unwrap_or(&ramoops.dmesg_zones[(count - 1) % ramoops.dmesg_zones.len()]);
+
+    let now = RealTimeClock::get().read_time();
+    let reason = match reason {
+        DumpReason::Oops => "Oops",
+        DumpReason::Panic => "Panic",
+    };
+    let mut header = DMESG_HEADER_PREFIX.to_vec();
+    header.extend_from_slice(
+        format!(
+            "{}.{:09}-D\n{}#{} Part1\n",
+            now.as_secs(),
+            now.subsec_nanos(),
+            reason,
+            count
+        )
+        .as_bytes(),
+    );
+
+    let logs_len = dmesg_zone.zone.capacity().saturating_sub(header.len());
+    let logs = match ramoops.console_zone.as_ref() {
+        Some(console_zone) => console_zone.try_read_tail(logs_len).unwrap_or_default(),
+        None => format!("{}\n", message).into_bytes(),
+    };
+
+    if dmesg_zone.zone.try_replace(&[&header, &logs]) {
+        dmesg_zone.has_new_record.store(true, Ordering::Relaxed);
+    }
+}
+
+fn write_console(output: &str) {
+    let Some(console_zone) = RAMOOPS
+        .get()
+        .and_then(|ramoops| ramoops.console_zone.as_ref())
+    else {
+        return;
+    };
+
+    if IS_PANICKING.load(Ordering::Relaxed) {
+        console_zone.try_write(output.as_bytes());
+    } else {
+        console_zone.write(output.as_bytes());
+    }
+}
+
+/// Parses the header of a dmesg record written by Linux or Asterinas.
+///
+/// The header is "====<seconds>.<nanoseconds>-<C|D>\n", where "C" means that the record is
+/// compressed. This function returns the time, whether the record is compressed, and the
+/// length of the header.
+fn parse_dmesg_header(data: &[u8]) -> Option<(Duration, bool, usize)> {
+    let rest = data.strip_prefix(DMESG_HEADER_PREFIX)?;
+    let line_len = rest.iter().position(|byte| *byte == b'\n')?;
+    let line = core::str::from_utf8(&rest[..line_len]).ok()?;
+
+    let (time, is_compressed) = match line.split_once('-') {
+        Some((time, "C")) => (time, true),
+        Some((time, "D")) => (time, false),
+        // The old versions of Linux do not record the compression.
+        None => (line, false),
+        Some(_) => return None,
+    };
+    let (secs, nanos) = time.split_once('.')?;
+    let time = Duration::new(secs.parse().ok()?, nanos.parse().ok()?);
+
+    Some((
+        time,
+        is_compressed,
+        DMESG_HEADER_PREFIX.len() + line_len + 1,
+    ))
+}
+
+/// Appends the ECC statistics to the record like Linux, if ECC is enabled.
+fn append_ecc_stats(data: &mut Vec<u8>, ecc_stats: Option<(usize, usize)>) {
+    match ecc_stats {
+        None => (),
+        Some((0, 0)) => data.extend_from_slice(b"\nECC: No errors detected\n"),
+        Some((corrected, bad_blocks)) => data.extend_from_slice(
+            format!(
+                "\nECC: {} Corrected bytes, {} unrecoverable blocks\n",
+                corrected, bad_blocks
+            )
+            .as_bytes(),
+        ),
+    }
+}
+
+fn round_down_pow_of_two(size: usize) -> usize {
+    if size == 0 {
+        0
+    } else {
+        1 << size.ilog2()
+    }
+}
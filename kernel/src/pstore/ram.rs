@@ -0,0 +1,280 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The persistent RAM zones.
+//!
+//! Each zone is a ring buffer in the ramoops region. The layout of the zones is the same as
+//! Linux, so the records written by one kernel can be read by the other:
+//!
+//! ```text
+//! +--------+------------------------+--------------------+-----------------+
+//! | header | data                   | ECC of data blocks | ECC of header   |
+//! +--------+------------------------+--------------------+-----------------+
+//! ```
+//!
+//! The header contains the signature, the start of the ring, and the size of the valid data.
+//! If ECC is enabled, each block of [`ECC_BLOCK_SIZE`] bytes of data and the header are
+//! protected by the Reed-Solomon codes (see [`super::ecc`]).
+
+use ostd::{io::IoMem, mm::VmIo};
+
+use super::ecc::ReedSolomon;
+use crate::prelude::*;
+
+/// The signature of valid zones ("DBGC").
+const PERSISTENT_RAM_SIG: u32 = 0x4347_4244;
+/// The size of the header, which consists of the signature, the start, and the size.
+const HEADER_SIZE: usize = 3 * size_of::<u32>();
+/// The size of the data blocks protected by ECC.
+pub(super) const ECC_BLOCK_SIZE: usize = 128;
+
+/// A zone in the persistent RAM.
+pub(super) struct RamZone {
+    mem: IoMem,
+    /// The offset of the zone in `mem`
+    base: usize,
+    /// The capacity of the data
+    buffer_size: usize,
+    ecc: Option<Arc<ReedSolomon>>,
+    /// The number of the data blocks protected by ECC
+    ecc_blocks: usize,
+    /// The start of the ring and the size of the valid data
+    state: SpinLock<(usize, usize)>,
+}
+
+/// The content of a zone that is saved before the zone is reused.
+pub(super) struct OldContent {
+    pub(super) data: Vec<u8>,
+    /// The number of the corrected bytes and the uncorrectable blocks, if ECC is enabled
+    pub(super) ecc_stats: Option<(usize, usize)>,
+}
+
+impl RamZone {
+    /// Creates a zone at `base` of `mem` with `size` bytes.
+    ///
+    /// This method returns `None` if the size is too small to contain any data.
+    pub(super) fn new(
+        mem: IoMem,
+        base: usize,
+        size: usize,
+        ecc: Option<Arc<ReedSolomon>>,
+    ) -> Option<Self> {
+        let mut buffer_size = size.checked_sub(HEADER_SIZE)?;
+        let mut ecc_blocks = 0;
+        if let Some(ecc) = ecc.as_ref() {
+            let ecc_size = ecc.nroots();
+            ecc_blocks = buffer_size
+                .checked_sub(ecc_size)?
+                .div_ceil(ECC_BLOCK_SIZE + ecc_size);
+            buffer_size = buffer_size.checked_sub((ecc_blocks + 1) * ecc_size)?;
+        }
+        if buffer_size == 0 {
+            return None;
+        }
+
+        Some(Self {
+            mem,
+            base,
+            buffer_size,
+            ecc,
+            ecc_blocks,
+            state: SpinLock::new((0, 0)),
+        })
+    }
+
+    /// Loads the content of the zone, which may be written in the previous boot.
+    ///
+    /// This method returns `None` if the zone has no valid content. Invalid zones are reset.
+    pub(super) fn load(&self) -> Option<OldContent> {
+        let mut header = [0u8; HEADER_SIZE];
+        self.mem.read_bytes(self.base, &mut header).unwrap();
+        let mut ecc_stats = self.ecc.as_ref().map(|_| (0, 0));
+        if let (Some(ecc), Some((corrected, bad_blocks))) = (self.ecc.as_ref(), ecc_stats.as_mut())
+        {
+            let mut parity = vec![0u8; ecc.nroots()];
+            self.mem
+                .read_bytes(self.header_parity_offset(), &mut parity)
+                .unwrap();
+            match ecc.decode(&mut header, &mut parity) {
+                Some(nr_errors) => *corrected += nr_errors,
+                None => *bad_blocks += 1,
+            }
+        }
+
+        let field = |index: usize| {
+            let bytes = &header[index * size_of::<u32>()..(index + 1) * size_of::<u32>()];
+            u32::from_le_bytes(bytes.try_into().unwrap()) as usize
+        };
+        let (sig, start, size) = (field(0) as u32, field(1), field(2));
+        if sig != PERSISTENT_RAM_SIG || size > self.buffer_size || start > size {
+            self.zap();
+            return None;
+        }
+        *self.state.disable_irq().lock() = (start, size);
+        if size == 0 {
+            return None;
+        }
+
+        // The parity of the last used block covers the whole block, even if it is partial.
+        let covered_size = (size.div_ceil(ECC_BLOCK_SIZE) * ECC_BLOCK_SIZE).min(self.buffer_size);
+        let mut buffer = vec![0u8; covered_size];
+        self.mem
+            .read_bytes(self.data_offset(), &mut buffer)
+            .unwrap();
+        if let (Some(ecc), Some((corrected, bad_blocks))) = (self.ecc.as_ref(), ecc_stats.as_mut())
+        {
+            let mut parity = vec![0u8; ecc.nroots()];
+            for (index, block) in buffer.chunks_mut(ECC_BLOCK_SIZE).enumerate() {
+                self.mem
+                    .read_bytes(self.block_parity_offset(index), &mut parity)
+                    .unwrap();
+                match ecc.decode(block, &mut parity) {
+                    Some(nr_errors) => *corrected += nr_errors,
+                    None => *bad_blocks += 1,
+                }
+            }
+        }
+        buffer.truncate(size);
+
+        // Put the oldest byte first.
+        buffer.rotate_left(start);
+        Some(OldContent {
+            data: buffer,
+            ecc_stats,
+        })
+    }
+
+    /// Resets the zone to be empty.
+    pub(super) fn zap(&self) {
+        let mut state = self.state.disable_irq().lock();
+        *state = (0, 0);
+        self.write_header(*state);
+    }
+
+    /// Appends the bytes to the ring, overwriting the oldest bytes if the ring is full.
+    pub(super) fn write(&self, bytes: &[u8]) {
+        let mut state = self.state.disable_irq().lock();
+        self.write_locked(&mut state, bytes);
+    }
+
+    /// Appends the bytes like [`Self::write`], or drops them if the zone is being written.
+    ///
+    /// This is used when the kernel panics, which may happen in the middle of a writing.
+    pub(super) fn try_write(&self, bytes: &[u8]) {
+        if let Some(mut state) = self.state.disable_irq().try_lock() {
+            self.write_locked(&mut state, bytes);
+        }
+    }
+
+    /// Replaces the content of the zone with the concatenated parts, or does nothing if the
+    /// zone is being written.
+    ///
+    /// This method returns whether the content is replaced.
+    pub(super) fn try_replace(&self, parts: &[&[u8]]) -> bool {
+        let Some(mut state) = self.state.disable_irq().try_lock() else {
+            return false;
+        };
+        *state = (0, 0);
+        for part in parts {
+            self.write_locked(&mut state, part);
+        }
+        true
+    }
+
+    /// Returns the last `len` bytes that have been written to the zone.
+    ///
+    /// This method returns `None` if the zone is being written.
+    pub(super) fn try_read_tail(&self, len: usize) -> Option<Vec<u8>> {
+        let (start, size) = *self.state.disable_irq().try_lock()?;
+        let mut buffer = vec![0u8; size];
+        self.mem
+            .read_bytes(self.data_offset(), &mut buffer)
+            .unwrap();
+        buffer.rotate_left(start);
+        buffer.drain(..size.saturating_sub(len));
+        Some(buffer)
+    }
+
+    /// Returns the capacity of the data.
+    pub(super) fn capacity(&self) -> usize {
+        self.buffer_size
+    }
+
+    fn write_locked(&self, state: &mut (usize, usize), mut bytes: &[u8]) {
+        if bytes.len() > self.buffer_size {
+            bytes = &bytes[bytes.len() - self.buffer_size..];
+        }
+
+        let (start, size) = *state;
+        let first_len = bytes.len().min(self.buffer_size - start);
+        self.write_data(start, &bytes[..first_len]);
+        self.write_data(0, &bytes[first_len..]);
+
+        *state = (
+            (start + bytes.len()) % self.buffer_size,
+            (size + bytes.len()).min(self.buffer_size),
+        );
+        self.write_header(*state);
+    }
+
+    fn write_data(&self, offset: usize, bytes: &[u8]) {
+        if bytes.is_empty() {
+            return;
+        }
+        self.mem
+            .write_bytes(self.data_offset() + offset, bytes)
+            .unwrap();
+
+        let Some(ecc) = self.ecc.as_ref() else {
+            return;
+        };
+        let mut parity = vec![0u8; ecc.nroots()];
+        let blocks = offset / ECC_BLOCK_SIZE..(offset + bytes.len()).div_ceil(ECC_BLOCK_SIZE);
+        for index in blocks {
+            let mut block = vec![0u8; self.block_len(index)];
+            self.mem
+                .read_bytes(self.data_offset() + index * ECC_BLOCK_SIZE, &mut block)
+                .unwrap();
+            ecc.encode(&block, &mut parity);
+            self.mem
+                .write_bytes(self.block_parity_offset(index), &parity)
+                .unwrap();
+        }
+    }
+
+    fn write_header(&self, (start, size): (usize, usize)) {
+        let mut header = [0u8; HEADER_SIZE];
+        for (field, value) in
+            header
+                .chunks_mut(size_of::<u32>())
+                .zip([PERSISTENT_RAM_SIG, start as u32, size as u32])
+        {
+            field.copy_from_slice(&value.to_le_bytes());
+        }
+        self.mem.write_bytes(self.base, &header).unwrap();
+
+        if let Some(ecc) = self.ecc.as_ref() {
+            let mut parity = vec![0u8; ecc.nroots()];
+            ecc.encode(&header, &mut parity);
+            self.mem
+                .write_bytes(self.header_parity_offset(), &parity)
+                .unwrap();
+        }
+    }
+
+    fn data_offset(&self) -> usize {
+        self.base + HEADER_SIZE
+    }
+
+    fn block_len(&self, index: usize) -> usize {
+        ECC_BLOCK_SIZE.min(self.buffer_size - index * ECC_BLOCK_SIZE)
+    }
+
+    fn block_parity_offset(&self, index: usize) -> usize {
+        let ecc_size = self.ecc.as_ref().unwrap().nroots();
+        self.data_offset() + self.buffer_size + index * ecc_size
+    }
+
+    fn header_parity_offset(&self) -> usize {
+        self.block_parity_offset(self.ecc_blocks)
+    }
+}
@@ -6,6 +6,6 @@ mod stats;
 
 pub use self::{
     nice::{AtomicNice, Nice},
-    sched_class::{init, RealTimePolicy, RealTimePriority, SchedAttr, SchedPolicy},
-    stats::{loadavg, nr_queued_and_running},
+    sched_class::{init, RealTimePolicy, RealTimePriority, SchedAttr, SchedPolicy, TaskSchedStat},
+    stats::{cpu_sched_stat, loadavg, nr_queued_and_running, pressure, CpuSchedStat},
 };
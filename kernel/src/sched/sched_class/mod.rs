@@ -3,10 +3,10 @@
 #![warn(unused)]
 
 use alloc::{boxed::Box, sync::Arc};
-use core::fmt;
+use core::{fmt, sync::atomic::Ordering};
 
 use ostd::{
-    arch::read_tsc as sched_clock,
+    arch::sched_clock,
     cpu::{all_cpus, CpuId, PinCurrentCpu},
     sync::SpinLock,
     task::{
@@ -21,11 +21,16 @@ use ostd::{
 
 use super::{
     nice::Nice,
-    stats::{set_stats_from_scheduler, SchedulerStats},
+    stats::{set_stats_from_scheduler, CpuSchedStat, SchedulerStats},
+};
+use crate::{
+    process::cgroup::cpuset::is_isolated_cpu,
+    thread::{AsThread, Thread},
+    trace::{trace_sched_migrate_task, trace_sched_wakeup, trace_sched_wakeup_latency},
 };
-use crate::thread::{AsThread, Thread};
 
 mod policy;
+mod sched_info;
 mod time;
 
 mod fair;
@@ -33,15 +38,21 @@ mod idle;
 mod real_time;
 mod stop;
 
-use self::policy::{SchedPolicyKind, SchedPolicyState};
 pub use self::{
     policy::SchedPolicy,
     real_time::{RealTimePolicy, RealTimePriority},
+    sched_info::TaskSchedStat,
+};
+use self::{
+    policy::{SchedPolicyKind, SchedPolicyState},
+    sched_info::{CpuSchedInfo, TaskSchedInfo},
 };
 
 type SchedEntity = (Arc<Task>, Arc<Thread>);
 
 pub fn init() {
+    time::init();
+
     let scheduler = Box::leak(Box::new(ClassScheduler::new()));
 
     // Inject the scheduler into the ostd for actual scheduling work.
@@ -70,6 +81,9 @@ struct PerCpuClassRqSet {
     fair: fair::FairClassRq,
     idle: idle::IdleClassRq,
     current: Option<(SchedEntity, CurrentRuntime)>,
+    /// The time (in the units of `sched_clock`) spent in running the tasks that are not idle.
+    busy_time: u64,
+    info: CpuSchedInfo,
 }
 
 /// Stores the runtime information of the current task.
@@ -133,6 +147,7 @@ pub struct SchedAttr {
     last_cpu: AtomicCpuId,
     real_time: real_time::RealTimeAttr,
     fair: fair::FairAttr,
+    info: TaskSchedInfo,
 }
 
 impl SchedAttr {
@@ -152,6 +167,7 @@ impl SchedAttr {
                 SchedPolicy::Fair(nice) => nice,
                 _ => Nice::default(),
             }),
+            info: TaskSchedInfo::default(),
         }
     }
 
@@ -182,6 +198,11 @@ impl SchedAttr {
         self.policy.update(f)
     }
 
+    /// Returns the scheduling statistics of the thread.
+    pub fn sched_stat(&self) -> TaskSchedStat {
+        self.info.stat()
+    }
+
     fn last_cpu(&self) -> Option<CpuId> {
         self.last_cpu.get()
     }
@@ -221,7 +242,23 @@ impl Scheduler for ClassScheduler {
                 thread.sched_attr().policy() < rq_current_thread.sched_attr().policy()
             });
 
-        thread.sched_attr().set_last_cpu(cpu);
+        let attr = thread.sched_attr();
+        if let Some(last_cpu) = attr.last_cpu().filter(|last_cpu| *last_cpu != cpu) {
+            attr.info.on_migrate();
+            trace_sched_migrate_task(&thread, last_cpu, cpu);
+        }
+        attr.set_last_cpu(cpu);
+
+        let is_wakeup = flags == EnqueueFlags::Wake;
+        attr.info.on_enqueue(sched_clock(), is_wakeup);
+        if is_wakeup {
+            rq.info.nr_wakeups += 1;
+            if disable_local().current_cpu() == cpu {
+                rq.info.nr_local_wakeups += 1;
+            }
+            trace_sched_wakeup(&thread, cpu);
+        }
+
         rq.enqueue_entity((task, thread), Some(flags));
 
         should_preempt.then_some(cpu)
@@ -248,6 +285,8 @@ impl ClassScheduler {
                 fair: fair::FairClassRq::new(cpu),
                 idle: idle::IdleClassRq::new(),
                 current: None,
+                busy_time: 0,
+                info: CpuSchedInfo::default(),
             })
         };
         ClassScheduler {
@@ -259,12 +298,24 @@ impl ClassScheduler {
     // TODO: Implement a better algorithm and replace the current naive implementation.
     fn select_cpu(&self, thread: &Thread, flags: EnqueueFlags) -> CpuId {
         if let Some(last_cpu) = thread.sched_attr().last_cpu() {
-            return last_cpu;
+            // The thread is moved away only if it is no longer allowed to run on the CPU, e.g.,
+            // after its cpuset is changed.
+            if thread
+                .atomic_cpu_affinity()
+                .contains(last_cpu, Ordering::Relaxed)
+            {
+                return last_cpu;
+            }
+        } else {
+            debug_assert!(flags == EnqueueFlags::Spawn);
         }
-        debug_assert!(flags == EnqueueFlags::Spawn);
         let guard = disable_local();
         let affinity = thread.atomic_cpu_affinity().load();
         let mut selected = guard.current_cpu();
+        // The CPUs of the isolated partitions are not balanced.
+        if affinity.contains(selected) && is_isolated_cpu(selected) {
+            return selected;
+        }
         let mut minimum_load = u32::MAX;
         let last_chosen = match self.last_chosen_cpu.get() {
             Some(cpu) => cpu.as_usize() as isize,
@@ -317,11 +368,37 @@ impl PerCpuClassRqSet {
         }
     }
 
+    /// Accounts a context switch to the thread.
+    fn account_switch(&mut self, next: &Thread, now: u64) {
+        let attr = next.sched_attr();
+        let (wait, is_wakeup) = attr.info.on_run(now);
+
+        self.info.nr_switches += 1;
+        if attr.policy_kind() == SchedPolicyKind::Idle {
+            self.info.nr_idle_switches += 1;
+        }
+        self.info.wait_time += wait;
+        self.info.nr_timeslices += 1;
+
+        if is_wakeup {
+            let cpu = attr.last_cpu().unwrap_or(CpuId::bsp());
+            trace_sched_wakeup_latency(next, cpu, time::clocks_to_ns(wait));
+        }
+    }
+
     fn nr_queued_and_running(&self) -> (u32, u32) {
         let queued = self.stop.len() + self.real_time.len() + self.fair.len() + self.idle.len();
         let running = usize::from(self.current.is_some());
         (queued as u32, running as u32)
     }
+
+    fn nr_waiting_and_busy(&self) -> (u32, u32) {
+        let waiting = self.stop.len() + self.real_time.len() + self.fair.len();
+        let is_busy = self.current.as_ref().is_some_and(|((_, thread), _)| {
+            thread.sched_attr().policy_kind() != SchedPolicyKind::Idle
+        });
+        (waiting as u32, u32::from(is_busy))
+    }
 }
 
 impl LocalRunQueue for PerCpuClassRqSet {
@@ -331,9 +408,13 @@ impl LocalRunQueue for PerCpuClassRqSet {
 
     fn pick_next_current(&mut self) -> Option<&Arc<Task>> {
         self.pick_next_entity().and_then(|next| {
+            let now = sched_clock();
+            self.account_switch(&next.1, now);
+
             // We guarantee that a task can appear at once in a `PerCpuClassRqSet`. So, the `next` cannot be the same
             // as the current task here.
             if let Some((old, _)) = self.current.replace((next, CurrentRuntime::new())) {
+                old.1.sched_attr().info.on_enqueue(now, false);
                 self.enqueue_entity(old, None);
             }
             self.current.as_ref().map(|((task, _), _)| task)
@@ -344,6 +425,14 @@ impl LocalRunQueue for PerCpuClassRqSet {
         if let Some(((_, cur), rt)) = &mut self.current {
             rt.update();
             let attr = &cur.sched_attr();
+            if attr.policy_kind() != SchedPolicyKind::Idle {
+                self.busy_time += rt.delta;
+            }
+            attr.info.on_update(rt.delta);
+            self.info.run_time += rt.delta;
+            if flags == UpdateFlags::Yield {
+                self.info.nr_yields += 1;
+            }
 
             let (current_expired, lookahead) = match attr.policy_kind() {
                 SchedPolicyKind::Stop => (self.stop.update_current(rt, attr, flags), 0),
@@ -362,7 +451,8 @@ impl LocalRunQueue for PerCpuClassRqSet {
     }
 
     fn dequeue_current(&mut self) -> Option<Arc<Task>> {
-        self.current.take().map(|((cur_task, _), _)| {
+        self.current.take().map(|((cur_task, cur_thread), _)| {
+            cur_thread.sched_attr().info.on_dequeue(sched_clock());
             cur_task.schedule_info().cpu.set_to_none();
             cur_task
         })
@@ -376,6 +466,21 @@ impl SchedulerStats for ClassScheduler {
             (queued + q, running + r)
         })
     }
+
+    fn nr_waiting_and_busy(&self) -> (u32, u32) {
+        self.rqs.iter().fold((0, 0), |(waiting, busy), rq| {
+            let (w, b) = rq.disable_irq().lock().nr_waiting_and_busy();
+            (waiting + w, busy + b)
+        })
+    }
+
+    fn busy_time(&self, cpu: CpuId) -> u64 {
+        self.rqs[cpu.as_usize()].disable_irq().lock().busy_time
+    }
+
+    fn cpu_sched_stat(&self, cpu: CpuId) -> CpuSchedStat {
+        self.rqs[cpu.as_usize()].disable_irq().lock().info.stat()
+    }
 }
 
 impl Default for ClassScheduler {
@@ -0,0 +1,142 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The scheduling statistics of the threads and the CPUs.
+//!
+//! The times are recorded in the units of `sched_clock`, and are converted to nanoseconds
+//! when they are reported.
+
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use super::time::clocks_to_ns;
+use crate::sched::stats::CpuSchedStat;
+
+/// The scheduling statistics of a thread.
+///
+/// The statistics are updated by the CPU whose run queue holds the thread.
+#[derive(Debug, Default)]
+pub(super) struct TaskSchedInfo {
+    run_time: AtomicU64,
+    wait_time: AtomicU64,
+    sleep_time: AtomicU64,
+    nr_timeslices: AtomicU64,
+    nr_migrations: AtomicU64,
+    nr_wakeups: AtomicU64,
+    wakeup_latency: AtomicU64,
+    max_wakeup_latency: AtomicU64,
+    /// The time when the thread is put into a run queue.
+    enqueued_at: AtomicU64,
+    /// The time when the thread stops running to sleep, or zero if it is not sleeping.
+    slept_at: AtomicU64,
+    /// Whether the thread is put into the run queue by a wakeup.
+    is_woken: AtomicBool,
+}
+
+impl TaskSchedInfo {
+    /// Records that the thread is put into a run queue.
+    pub(super) fn on_enqueue(&self, now: u64, is_wakeup: bool) {
+        if is_wakeup {
+            let slept_at = self.slept_at.swap(0, Ordering::Relaxed);
+            if slept_at != 0 {
+                self.sleep_time
+                    .fetch_add(now.saturating_sub(slept_at), Ordering::Relaxed);
+            }
+            self.nr_wakeups.fetch_add(1, Ordering::Relaxed);
+        }
+        self.is_woken.store(is_wakeup, Ordering::Relaxed);
+        self.enqueued_at.store(now, Ordering::Relaxed);
+    }
+
+    /// Records that the thread starts running.
+    ///
+    /// Returns the time spent on waiting, and whether the time is the latency of a wakeup.
+    pub(super) fn on_run(&self, now: u64) -> (u64, bool) {
+        let wait = now.saturating_sub(self.enqueued_at.load(Ordering::Relaxed));
+        self.wait_time.fetch_add(wait, Ordering::Relaxed);
+        self.nr_timeslices.fetch_add(1, Ordering::Relaxed);
+
+        if !self.is_woken.swap(false, Ordering::Relaxed) {
+            return (wait, false);
+        }
+        self.wakeup_latency.fetch_add(wait, Ordering::Relaxed);
+        self.max_wakeup_latency.fetch_max(wait, Ordering::Relaxed);
+        (wait, true)
+    }
+
+    /// Records that the thread has run for `delta`.
+    pub(super) fn on_update(&self, delta: u64) {
+        self.run_time.fetch_add(delta, Ordering::Relaxed);
+    }
+
+    /// Records that the thread stops running to sleep (or to exit).
+    pub(super) fn on_dequeue(&self, now: u64) {
+        self.slept_at.store(now, Ordering::Relaxed);
+    }
+
+    /// Records that the thread is moved to another CPU.
+    pub(super) fn on_migrate(&self) {
+        self.nr_migrations.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn stat(&self) -> TaskSchedStat {
+        let load = |value: &AtomicU64| value.load(Ordering::Relaxed);
+        TaskSchedStat {
+            run_time_ns: clocks_to_ns(load(&self.run_time)),
+            wait_time_ns: clocks_to_ns(load(&self.wait_time)),
+            sleep_time_ns: clocks_to_ns(load(&self.sleep_time)),
+            nr_timeslices: load(&self.nr_timeslices),
+            nr_migrations: load(&self.nr_migrations),
+            nr_wakeups: load(&self.nr_wakeups),
+            wakeup_latency_ns: clocks_to_ns(load(&self.wakeup_latency)),
+            max_wakeup_latency_ns: clocks_to_ns(load(&self.max_wakeup_latency)),
+        }
+    }
+}
+
+/// The scheduling statistics of a thread, which are reported in `/proc/[pid]/schedstat`.
+#[derive(Debug, Clone, Copy)]
+pub struct TaskSchedStat {
+    /// The time spent on running.
+    pub run_time_ns: u64,
+    /// The time spent on waiting in the run queues.
+    pub wait_time_ns: u64,
+    /// The time spent on sleeping.
+    pub sleep_time_ns: u64,
+    /// The number of the times that the thread is picked to run.
+    pub nr_timeslices: u64,
+    /// The number of the times that the thread is moved to another CPU.
+    pub nr_migrations: u64,
+    /// The number of the times that the thread is woken.
+    pub nr_wakeups: u64,
+    /// The total time from the wakeups to running.
+    pub wakeup_latency_ns: u64,
+    /// The longest time from a wakeup to running.
+    pub max_wakeup_latency_ns: u64,
+}
+
+/// The scheduling statistics of a CPU.
+#[derive(Debug, Default)]
+pub(super) struct CpuSchedInfo {
+    pub(super) nr_yields: u64,
+    pub(super) nr_switches: u64,
+    pub(super) nr_idle_switches: u64,
+    pub(super) nr_wakeups: u64,
+    pub(super) nr_local_wakeups: u64,
+    pub(super) run_time: u64,
+    pub(super) wait_time: u64,
+    pub(super) nr_timeslices: u64,
+}
+
+impl CpuSchedInfo {
+    pub(super) fn stat(&self) -> CpuSchedStat {
+        CpuSchedStat {
+            nr_yields: self.nr_yields,
+            nr_switches: self.nr_switches,
+            nr_idle_switches: self.nr_idle_switches,
+            nr_wakeups: self.nr_wakeups,
+            nr_local_wakeups: self.nr_local_wakeups,
+            run_time_ns: clocks_to_ns(self.run_time),
+            wait_time_ns: clocks_to_ns(self.wait_time),
+            nr_timeslices: self.nr_timeslices,
+        }
+    }
+}
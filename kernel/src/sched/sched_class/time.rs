@@ -2,6 +2,7 @@
 
 use core::mem;
 
+use ostd::boot::param::{register_param, BootParam};
 use spin::Once;
 
 /// Returns the numerator and denominator of the ratio R:
@@ -29,16 +30,28 @@ fn tsc_factors() -> (u64, u64) {
 }
 
 /// The base time slice allocated for every thread, measured in nanoseconds.
-pub const BASE_SLICE_NS: u64 = 750_000;
+///
+/// It can be set by `sched.base_slice_ns=` in the kernel command line.
+pub static BASE_SLICE_NS: BootParam<u64> = BootParam::new("sched", "base_slice_ns", 750_000);
 
 /// The minimum scheduling period, measured in nanoseconds.
-pub const MIN_PERIOD_NS: u64 = 6_000_000;
+///
+/// It can be set by `sched.latency_ns=` in the kernel command line.
+pub static MIN_PERIOD_NS: BootParam<u64> = BootParam::new("sched", "latency_ns", 6_000_000);
+
+/// Registers the boot-time parameters of the time slices.
+///
+/// This should be called before the time slices are used.
+pub fn init() {
+    register_param(&BASE_SLICE_NS);
+    register_param(&MIN_PERIOD_NS);
+}
 
 fn consts() -> (u64, u64) {
     static CONSTS: Once<(u64, u64)> = Once::new();
     *CONSTS.call_once(|| {
         let (a, b) = tsc_factors();
-        (BASE_SLICE_NS * b / a, MIN_PERIOD_NS * b / a)
+        (BASE_SLICE_NS.get() * b / a, MIN_PERIOD_NS.get() * b / a)
     })
 }
 
@@ -51,3 +64,9 @@ pub fn base_slice_clocks() -> u64 {
 pub fn min_period_clocks() -> u64 {
     consts().1
 }
+
+/// Converts the time measured in TSC clock units to nanoseconds.
+pub fn clocks_to_ns(clocks: u64) -> u64 {
+    let (a, b) = tsc_factors();
+    (clocks as u128 * a as u128 / b as u128) as u64
+}
@@ -1,6 +1,10 @@
 // SPDX-License-Identifier: MPL-2.0
 
 pub mod loadavg;
+pub mod pressure;
 mod scheduler_stats;
+mod utilization;
 
-pub use scheduler_stats::{nr_queued_and_running, set_stats_from_scheduler, SchedulerStats};
+pub use scheduler_stats::{
+    cpu_sched_stat, nr_queued_and_running, set_stats_from_scheduler, CpuSchedStat, SchedulerStats,
+};
@@ -0,0 +1,366 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! This module implements the pressure stall information (PSI).
+//!
+//! The pressure of a resource is the share of the time in which the tasks are stalled by the
+//! lack of the resource. "Some" pressure means that at least one task is stalled, and "full"
+//! pressure means that the tasks are stalled while no CPU is running any task that is not
+//! idle. The states are sampled periodically:
+//!  - CPU: some tasks are waiting in the run queues (the full pressure is always zero, like
+//!    Linux's system-wide CPU pressure);
+//!  - Memory: some tasks are in the stalls of [`enter_stall`];
+//!  - I/O: some tasks are waiting for the block I/O requests.
+//!
+//! There is no memory reclaim yet, so nothing enters the memory stalls and the memory pressure
+//! stays zero for now.
+//!
+//! The user space can also create [`Trigger`]s, which are notified when the stall time
+//! accumulated in a time window exceeds a threshold.
+//!
+//! Reference: <https://docs.kernel.org/accounting/psi.html>
+
+use core::sync::atomic::{
+    AtomicBool, AtomicU32, AtomicU64,
+    Ordering::{self, Relaxed},
+};
+
+use ostd::{
+    arch::timer::TIMER_FREQ,
+    sync::{LocalIrqDisabled, SpinLock},
+    timer,
+};
+
+use super::loadavg::FixedPoint;
+use crate::{
+    events::IoEvents,
+    prelude::*,
+    process::signal::{PollHandle, Pollee},
+};
+
+/// 10 ms intervals (in jiffies)
+const SAMPLE_FREQ: u64 = if TIMER_FREQ >= 100 {
+    TIMER_FREQ / 100
+} else {
+    1
+};
+/// 2 sec intervals
+const AVG_FREQ: u64 = 2 * TIMER_FREQ + 1;
+/// 1/exp(2sec/10sec) as fixed-point
+const EXP_10S: FixedPoint = FixedPoint::from_bits(1677);
+/// 1/exp(2sec/60sec)
+const EXP_60S: FixedPoint = FixedPoint::from_bits(1981);
+/// 1/exp(2sec/300sec)
+const EXP_300S: FixedPoint = FixedPoint::from_bits(2034);
+
+const NSEC_PER_SEC: u64 = 1_000_000_000;
+
+/// The resources whose pressure is tracked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resource {
+    Cpu,
+    Memory,
+    Io,
+}
+
+impl Resource {
+    pub const ALL: [Self; 3] = [Self::Cpu, Self::Memory, Self::Io];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Cpu => "cpu",
+            Self::Memory => "memory",
+            Self::Io => "io",
+        }
+    }
+}
+
+/// The kinds of the pressure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StallKind {
+    /// At least one task is stalled.
+    Some,
+    /// The tasks are stalled, and no CPU is doing productive work.
+    Full,
+}
+
+/// The pressure of a resource, which is reported in `/proc/pressure/*`.
+#[derive(Debug, Clone, Copy)]
+pub struct Pressure {
+    /// The percentages of the stall time in the last 10, 60 and 300 seconds.
+    pub avg: [FixedPoint; 3],
+    /// The total stall time.
+    pub total_ns: u64,
+}
+
+/// The number of the tasks that are stalled by memory and I/O, respectively.
+static NR_STALLED: [AtomicU32; 2] = [AtomicU32::new(0), AtomicU32::new(0)];
+
+fn nr_stalled(resource: Resource) -> &'static AtomicU32 {
+    match resource {
+        Resource::Cpu => panic!("the CPU stalls are sampled from the scheduler"),
+        Resource::Memory => &NR_STALLED[0],
+        Resource::Io => &NR_STALLED[1],
+    }
+}
+
+/// Marks the current task as stalled by the resource, until the returned guard is dropped.
+///
+/// # Panics
+///
+/// This function panics if the resource is [`Resource::Cpu`], whose stalls are sampled from
+/// the run queues.
+pub fn enter_stall(resource: Resource) -> StallGuard {
+    nr_stalled(resource).fetch_add(1, Relaxed);
+    StallGuard(resource)
+}
+
+/// A guard that marks the current task as stalled (see [`enter_stall`]).
+#[must_use]
+pub struct StallGuard(Resource);
+
+impl Drop for StallGuard {
+    fn drop(&mut self) {
+        nr_stalled(self.0).fetch_sub(1, Relaxed);
+    }
+}
+
+/// Registers the hooks to account the waits for the block I/O requests.
+pub(super) fn init() {
+    aster_block::bio::register_wait_hooks(
+        || {
+            nr_stalled(Resource::Io).fetch_add(1, Relaxed);
+        },
+        || {
+            nr_stalled(Resource::Io).fetch_sub(1, Relaxed);
+        },
+    );
+}
+
+/// The accumulated states of a kind of the pressure of a resource.
+#[derive(Clone, Copy)]
+struct StallStat {
+    total_ns: u64,
+    /// The total stall time when the averages were updated last time.
+    total_ns_at_avg: u64,
+    avg: [FixedPoint; 3],
+}
+
+impl StallStat {
+    const fn new() -> Self {
+        Self {
+            total_ns: 0,
+            total_ns_at_avg: 0,
+            avg: [FixedPoint::ZERO; 3],
+        }
+    }
+
+    fn update_avg(&mut self, period_ns: u64) {
+        let stalled_ns = (self.total_ns - self.total_ns_at_avg).min(period_ns);
+        self.total_ns_at_avg = self.total_ns;
+
+        // The bits of the percentage in the fixed-point representation.
+        let percent_bits = stalled_ns * 100 * FixedPoint::ONE.to_bits() as u64 / period_ns.max(1);
+        let percent = FixedPoint::from_bits(percent_bits as u32);
+
+        for (avg, exp) in self.avg.iter_mut().zip([EXP_10S, EXP_60S, EXP_300S]) {
+            *avg = *avg * exp + percent * (FixedPoint::ONE - exp);
+        }
+    }
+}
+
+struct PressureState {
+    /// The time when the states were sampled last time (in jiffies).
+    last_sample: u64,
+    /// The time when the averages were updated last time (in jiffies).
+    last_avg: u64,
+    /// The statistics of the some and full pressure of the resources, in the order of
+    /// [`Resource::ALL`].
+    stats: [[StallStat; 2]; 3],
+}
+
+static STATE: SpinLock<PressureState, LocalIrqDisabled> = SpinLock::new(PressureState {
+    last_sample: 0,
+    last_avg: 0,
+    stats: [[StallStat::new(); 2]; 3],
+});
+
+/// Next time the states will be sampled (in jiffies).
+static NEXT_SAMPLE: AtomicU64 = AtomicU64::new(0);
+
+/// The triggers created by the user space.
+static TRIGGERS: SpinLock<Vec<Weak<Trigger>>, LocalIrqDisabled> = SpinLock::new(Vec::new());
+
+/// Returns the pressure of the resource.
+pub fn get_pressure(resource: Resource, kind: StallKind) -> Pressure {
+    let stat = STATE.lock().stats[resource as usize][kind as usize];
+    Pressure {
+        avg: stat.avg,
+        total_ns: stat.total_ns,
+    }
+}
+
+/// Samples the states of the stalls, and updates the pressure of the resources.
+///
+/// This function should be called periodically. The `nr_waiting_and_busy` function should
+/// return the number of the tasks that are waiting in the run queues, and the number of the
+/// CPUs that are running the tasks that are not idle.
+pub(super) fn update_pressure<F>(nr_waiting_and_busy: F)
+where
+    F: Fn() -> (u32, u32),
+{
+    let jiffies = timer::Jiffies::elapsed().as_u64();
+    if jiffies < NEXT_SAMPLE.load(Relaxed) {
+        return;
+    }
+    NEXT_SAMPLE.store(jiffies + SAMPLE_FREQ, Relaxed);
+
+    let (nr_waiting, nr_busy) = nr_waiting_and_busy();
+    let is_stalled = |resource: Resource| {
+        let is_some = match resource {
+            Resource::Cpu => nr_waiting > 0,
+            _ => nr_stalled(resource).load(Relaxed) > 0,
+        };
+        let is_full = resource != Resource::Cpu && is_some && nr_busy == 0;
+        [is_some, is_full]
+    };
+
+    let mut state = STATE.lock();
+    if state.last_sample == 0 {
+        // The states of the time before the first sample are unknown.
+        state.last_sample = jiffies;
+        state.last_avg = jiffies;
+        return;
+    }
+    let elapsed_ns = (jiffies - state.last_sample) * NSEC_PER_SEC / TIMER_FREQ;
+    state.last_sample = jiffies;
+
+    for resource in Resource::ALL {
+        let stalls = is_stalled(resource);
+        for (stat, is_stalled) in state.stats[resource as usize].iter_mut().zip(stalls) {
+            if is_stalled {
+                stat.total_ns += elapsed_ns;
+            }
+        }
+    }
+
+    if jiffies - state.last_avg >= AVG_FREQ {
+        let period_ns = (jiffies - state.last_avg) * NSEC_PER_SEC / TIMER_FREQ;
+        state.last_avg = jiffies;
+        for stat in state.stats.iter_mut().flatten() {
+            stat.update_avg(period_ns);
+        }
+    }
+
+    let now_ns = jiffies * NSEC_PER_SEC / TIMER_FREQ;
+    let stats = state.stats;
+    drop(state);
+
+    let mut triggers = TRIGGERS.lock();
+    triggers.retain(|trigger| {
+        let Some(trigger) = trigger.upgrade() else {
+            return false;
+        };
+        let stat = &stats[trigger.resource as usize][trigger.kind as usize];
+        trigger.update(now_ns, stat.total_ns);
+        true
+    });
+}
+
+/// A trigger that is notified when the stall time accumulated in a time window exceeds a
+/// threshold.
+///
+/// The trigger is notified at most once in each window. It is removed when it is dropped.
+pub struct Trigger {
+    resource: Resource,
+    kind: StallKind,
+    threshold_ns: u64,
+    window_ns: u64,
+    window: SpinLock<TriggerWindow, LocalIrqDisabled>,
+    /// Whether the trigger has been notified since the last poll.
+    has_event: AtomicBool,
+    pollee: Pollee,
+}
+
+struct TriggerWindow {
+    /// The start of the window, or `None` if the window has not started.
+    start_ns: Option<u64>,
+    /// The total stall time at the start of the window.
+    start_total_ns: u64,
+    /// Whether the trigger has been notified in the window.
+    has_fired: bool,
+}
+
+impl Trigger {
+    /// The minimal time window.
+    pub const MIN_WINDOW_NS: u64 = 500_000_000;
+    /// The maximal time window.
+    pub const MAX_WINDOW_NS: u64 = 10 * NSEC_PER_SEC;
+
+    /// Creates a trigger.
+    pub fn new(
+        resource: Resource,
+        kind: StallKind,
+        threshold_ns: u64,
+        window_ns: u64,
+    ) -> Result<Arc<Self>> {
+        if !(Self::MIN_WINDOW_NS..=Self::MAX_WINDOW_NS).contains(&window_ns) {
+            return_errno_with_message!(Errno::EINVAL, "the time window is out of range");
+        }
+        if threshold_ns == 0 || threshold_ns > window_ns {
+            return_errno_with_message!(Errno::EINVAL, "the threshold is invalid");
+        }
+
+        let trigger = Arc::new(Self {
+            resource,
+            kind,
+            threshold_ns,
+            window_ns,
+            window: SpinLock::new(TriggerWindow {
+                start_ns: None,
+                start_total_ns: 0,
+                has_fired: false,
+            }),
+            has_event: AtomicBool::new(false),
+            pollee: Pollee::new(),
+        });
+        TRIGGERS.lock().push(Arc::downgrade(&trigger));
+        Ok(trigger)
+    }
+
+    fn update(&self, now_ns: u64, total_ns: u64) {
+        let mut window = self.window.lock();
+        match window.start_ns {
+            Some(start_ns) if now_ns - start_ns < self.window_ns => {}
+            _ => {
+                window.start_ns = Some(now_ns);
+                window.start_total_ns = total_ns;
+                window.has_fired = false;
+            }
+        }
+
+        if window.has_fired || total_ns - window.start_total_ns < self.threshold_ns {
+            return;
+        }
+        window.has_fired = true;
+        drop(window);
+
+        self.has_event.store(true, Ordering::Release);
+        self.pollee.notify(IoEvents::PRI);
+    }
+
+    /// Polls the trigger, which reports [`IoEvents::PRI`] once after each notification.
+    pub fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
+        let events = self.pollee.poll_with(mask, poller, || {
+            if self.has_event.swap(false, Ordering::Acquire) {
+                IoEvents::PRI
+            } else {
+                IoEvents::empty()
+            }
+        });
+        if events.contains(IoEvents::PRI) {
+            // The event is consumed, so it must not be cached.
+            self.pollee.invalidate();
+        }
+        events
+    }
+}
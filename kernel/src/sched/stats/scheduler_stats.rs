@@ -1,9 +1,9 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use ostd::timer;
+use ostd::{cpu::CpuId, timer};
 use spin::Once;
 
-use super::loadavg;
+use super::{loadavg, pressure, utilization};
 
 /// The global scheduler statistic singleton
 static SCHEDULER_STATS: Once<&'static dyn SchedulerStats> = Once::new();
@@ -16,9 +16,14 @@ static SCHEDULER_STATS: Once<&'static dyn SchedulerStats> = Once::new();
 pub fn set_stats_from_scheduler(scheduler: &'static dyn SchedulerStats) {
     SCHEDULER_STATS.call_once(|| scheduler);
 
-    // Register a callback to update the load average periodically
+    pressure::init();
+
+    // Register a callback to update the load average, the utilization and the pressure
+    // periodically
     timer::register_callback(|| {
         loadavg::update_loadavg(|| nr_queued_and_running().0);
+        utilization::update_utilization(busy_time);
+        pressure::update_pressure(nr_waiting_and_busy);
     });
 }
 
@@ -29,9 +34,56 @@ pub trait SchedulerStats: Sync + Send {
     /// We decided to return a tuple instead of having two separate functions to
     /// avoid the overhead of disabling the preemption twice to inspect the scheduler.
     fn nr_queued_and_running(&self) -> (u32, u32);
+
+    /// Returns a tuple with the number of tasks that are waiting in the runqueues (excluding
+    /// the idle tasks) and the number of CPUs that are running the tasks that are not idle.
+    fn nr_waiting_and_busy(&self) -> (u32, u32);
+
+    /// Returns the time (in the units of `sched_clock`) that the CPU spends in running the
+    /// tasks that are not idle.
+    fn busy_time(&self, cpu: CpuId) -> u64;
+
+    /// Returns the scheduling statistics of the CPU.
+    fn cpu_sched_stat(&self, cpu: CpuId) -> CpuSchedStat;
+}
+
+/// The scheduling statistics of a CPU, which are reported in `/proc/schedstat`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CpuSchedStat {
+    /// The number of the times that the tasks yield the CPU.
+    pub nr_yields: u64,
+    /// The number of the context switches.
+    pub nr_switches: u64,
+    /// The number of the context switches to the idle tasks.
+    pub nr_idle_switches: u64,
+    /// The number of the wakeups that put the tasks into the run queue of the CPU.
+    pub nr_wakeups: u64,
+    /// The number of the wakeups that are done by the CPU itself.
+    pub nr_local_wakeups: u64,
+    /// The time spent on running the tasks.
+    pub run_time_ns: u64,
+    /// The time that the tasks spend on waiting in the run queue.
+    pub wait_time_ns: u64,
+    /// The number of the times that the tasks are picked to run.
+    pub nr_timeslices: u64,
 }
 
 /// Get the amount of tasks in the runqueues and the amount of running tasks.
 pub fn nr_queued_and_running() -> (u32, u32) {
     SCHEDULER_STATS.get().unwrap().nr_queued_and_running()
 }
+
+/// Get the amount of tasks waiting in the runqueues and the amount of busy CPUs.
+pub fn nr_waiting_and_busy() -> (u32, u32) {
+    SCHEDULER_STATS.get().unwrap().nr_waiting_and_busy()
+}
+
+/// Get the time that the CPU spends in running the tasks that are not idle.
+pub fn busy_time(cpu: CpuId) -> u64 {
+    SCHEDULER_STATS.get().unwrap().busy_time(cpu)
+}
+
+/// Get the scheduling statistics of the CPU.
+pub fn cpu_sched_stat(cpu: CpuId) -> CpuSchedStat {
+    SCHEDULER_STATS.get().unwrap().cpu_sched_stat(cpu)
+}
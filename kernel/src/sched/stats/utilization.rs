@@ -0,0 +1,59 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! This module samples the utilization of the CPUs, which drives the CPU frequency scaling.
+//!
+//! The utilization of a CPU is the percentage of the time spent in running the tasks that are
+//! not idle since the last sample.
+
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, Ordering::Relaxed};
+
+use ostd::{
+    arch::{sched_clock, timer::TIMER_FREQ},
+    cpu::{all_cpus, CpuId},
+    sync::{LocalIrqDisabled, SpinLock},
+    timer,
+};
+
+/// The last samples of the CPUs, i.e., the `sched_clock` and the busy time.
+static LAST_SAMPLES: SpinLock<Vec<(u64, u64)>, LocalIrqDisabled> = SpinLock::new(Vec::new());
+
+/// Next time the utilization will be sampled (in jiffies).
+static NEXT_SAMPLE: AtomicU64 = AtomicU64::new(0);
+
+/// Samples the utilization of the CPUs, and reports it to the CPU frequency policies.
+///
+/// This function should be called periodically. The `busy_time` function should return the
+/// busy time of the CPU.
+pub(super) fn update_utilization<F>(busy_time: F)
+where
+    F: Fn(CpuId) -> u64,
+{
+    let jiffies = timer::Jiffies::elapsed().as_u64();
+    if jiffies < NEXT_SAMPLE.load(Relaxed) {
+        return;
+    }
+    let interval = aster_cpufreq::sampling_rate_ms() as u64 * TIMER_FREQ / 1000;
+    NEXT_SAMPLE.store(jiffies + interval.max(1), Relaxed);
+
+    let now = sched_clock();
+    let mut samples = LAST_SAMPLES.lock();
+    if samples.is_empty() {
+        // There is no utilization until the second sample.
+        samples.extend(all_cpus().map(|cpu| (now, busy_time(cpu))));
+        return;
+    }
+
+    let utilization: Vec<u32> = all_cpus()
+        .map(|cpu| {
+            let busy = busy_time(cpu);
+            let (last_clock, last_busy) =
+                core::mem::replace(&mut samples[cpu.as_usize()], (now, busy));
+            let elapsed = now.saturating_sub(last_clock).max(1);
+            (busy.saturating_sub(last_busy) * 100 / elapsed).min(100) as u32
+        })
+        .collect();
+    drop(samples);
+
+    aster_cpufreq::update_utilization(|cpu| utilization[cpu.as_usize()]);
+}
@@ -0,0 +1,212 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The security hooks, through which the security modules are consulted.
+//!
+//! The kernel calls the hook functions in this module at the points where an operation may be
+//! denied by a security policy, e.g., before a file is opened or a file system is mounted. Every
+//! registered [`SecurityModule`] is consulted in the order that it is registered, and the
+//! operation is denied once any module denies it. So the modules are stacked: each module only
+//! restricts what is allowed by the discretionary access control and by the other modules.
+//!
+//! The hooks run after the discretionary access control checks, so a module is only consulted
+//! for the operations that would be allowed without it.
+
+use crate::{
+    fs::{
+        path::Dentry,
+        utils::{AccessMode, InodeType},
+    },
+    net::socket::{Socket, SocketAddr},
+    prelude::*,
+    process::CloneFlags,
+    util::net::{CSocketAddrFamily, SockType},
+    vm::perms::VmPerms,
+};
+
+/// A security module, which implements a security policy by denying some operations.
+///
+/// Every hook returns `Ok(())` by default, so a module only implements the hooks that it cares
+/// about.
+pub trait SecurityModule: Send + Sync + Debug {
+    /// Returns the name of the module.
+    fn name(&self) -> &'static str;
+
+    /// Checks whether a new thread or process can be created with the flags.
+    fn task_create(&self, _flags: CloneFlags) -> Result<()> {
+        Ok(())
+    }
+
+    /// Checks whether the file can be executed as a new program.
+    fn bprm_check(&self, _dentry: &Dentry) -> Result<()> {
+        Ok(())
+    }
+
+    /// Checks whether the file can be opened with the access mode.
+    fn file_open(&self, _dentry: &Dentry, _access_mode: AccessMode) -> Result<()> {
+        Ok(())
+    }
+
+    /// Checks whether the memory can be mapped with the permissions.
+    ///
+    /// The file is `None` for anonymous mappings.
+    fn mmap_file(&self, _dentry: Option<&Dentry>, _perms: VmPerms) -> Result<()> {
+        Ok(())
+    }
+
+    /// Checks whether a file of the type can be created in the directory.
+    fn inode_create(&self, _dir: &Dentry, _type_: InodeType) -> Result<()> {
+        Ok(())
+    }
+
+    /// Checks whether the file can be linked into the directory.
+    fn inode_link(&self, _old: &Dentry, _new_dir: &Dentry) -> Result<()> {
+        Ok(())
+    }
+
+    /// Checks whether the file of the name can be unlinked from the directory.
+    fn inode_unlink(&self, _dir: &Dentry, _name: &str) -> Result<()> {
+        Ok(())
+    }
+
+    /// Checks whether the directory of the name can be removed from the directory.
+    fn inode_rmdir(&self, _dir: &Dentry, _name: &str) -> Result<()> {
+        Ok(())
+    }
+
+    /// Checks whether the file can be renamed (or exchanged if `is_exchange` is true).
+    fn inode_rename(
+        &self,
+        _old_dir: &Dentry,
+        _old_name: &str,
+        _new_dir: &Dentry,
+        _new_name: &str,
+        _is_exchange: bool,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// Checks whether a socket can be created.
+    fn socket_create(
+        &self,
+        _domain: CSocketAddrFamily,
+        _type_: SockType,
+        _protocol: i32,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// Checks whether the socket can be bound to the address.
+    fn socket_bind(&self, _socket: &dyn Socket, _addr: &SocketAddr) -> Result<()> {
+        Ok(())
+    }
+
+    /// Checks whether the socket can be connected to the address.
+    fn socket_connect(&self, _socket: &dyn Socket, _addr: &SocketAddr) -> Result<()> {
+        Ok(())
+    }
+
+    /// Checks whether the socket can listen for connections.
+    fn socket_listen(&self, _socket: &dyn Socket, _backlog: usize) -> Result<()> {
+        Ok(())
+    }
+
+    /// Checks whether a mount operation with the flags can be performed on the target.
+    ///
+    /// The device name is interpreted according to the flags, as in `mount(2)`.
+    fn sb_mount(&self, _dev_name: &str, _target: &Dentry, _flags: u32) -> Result<()> {
+        Ok(())
+    }
+
+    /// Checks whether the mount at the target can be unmounted.
+    fn sb_umount(&self, _target: &Dentry) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// The registered modules.
+///
+/// This is a sleeping lock because the modules may sleep in their hooks, e.g., to look up files.
+static MODULES: RwMutex<Vec<Arc<dyn SecurityModule>>> = RwMutex::new(Vec::new());
+
+/// Registers a security module, which is consulted after the registered ones.
+pub fn register_module(module: Arc<dyn SecurityModule>) {
+    info!(
+        "[kernel] security module \"{}\" is registered",
+        module.name()
+    );
+    MODULES.write().push(module);
+}
+
+/// Consults every registered module in order, stopping at the first denial.
+fn call_hooks(mut hook: impl FnMut(&dyn SecurityModule) -> Result<()>) -> Result<()> {
+    for module in MODULES.read().iter() {
+        hook(module.as_ref())?;
+    }
+    Ok(())
+}
+
+pub fn task_create(flags: CloneFlags) -> Result<()> {
+    call_hooks(|module| module.task_create(flags))
+}
+
+pub fn bprm_check(dentry: &Dentry) -> Result<()> {
+    call_hooks(|module| module.bprm_check(dentry))
+}
+
+pub fn file_open(dentry: &Dentry, access_mode: AccessMode) -> Result<()> {
+    call_hooks(|module| module.file_open(dentry, access_mode))
+}
+
+pub fn mmap_file(dentry: Option<&Dentry>, perms: VmPerms) -> Result<()> {
+    call_hooks(|module| module.mmap_file(dentry, perms))
+}
+
+pub fn inode_create(dir: &Dentry, type_: InodeType) -> Result<()> {
+    call_hooks(|module| module.inode_create(dir, type_))
+}
+
+pub fn inode_link(old: &Dentry, new_dir: &Dentry) -> Result<()> {
+    call_hooks(|module| module.inode_link(old, new_dir))
+}
+
+pub fn inode_unlink(dir: &Dentry, name: &str) -> Result<()> {
+    call_hooks(|module| module.inode_unlink(dir, name))
+}
+
+pub fn inode_rmdir(dir: &Dentry, name: &str) -> Result<()> {
+    call_hooks(|module| module.inode_rmdir(dir, name))
+}
+
+pub fn inode_rename(
+    old_dir: &Dentry,
+    old_name: &str,
+    new_dir: &Dentry,
+    new_name: &str,
+    is_exchange: bool,
+) -> Result<()> {
+    call_hooks(|module| module.inode_rename(old_dir, old_name, new_dir, new_name, is_exchange))
+}
+
+pub fn socket_create(domain: CSocketAddrFamily, type_: SockType, protocol: i32) -> Result<()> {
+    call_hooks(|module| module.socket_create(domain, type_, protocol))
+}
+
+pub fn socket_bind(socket: &dyn Socket, addr: &SocketAddr) -> Result<()> {
+    call_hooks(|module| module.socket_bind(socket, addr))
+}
+
+pub fn socket_connect(socket: &dyn Socket, addr: &SocketAddr) -> Result<()> {
+    call_hooks(|module| module.socket_connect(socket, addr))
+}
+
+pub fn socket_listen(socket: &dyn Socket, backlog: usize) -> Result<()> {
+    call_hooks(|module| module.socket_listen(socket, backlog))
+}
+
+pub fn sb_mount(dev_name: &str, target: &Dentry, flags: u32) -> Result<()> {
+    call_hooks(|module| module.sb_mount(dev_name, target, flags))
+}
+
+pub fn sb_umount(target: &Dentry) -> Result<()> {
+    call_hooks(|module| module.sb_umount(target))
+}
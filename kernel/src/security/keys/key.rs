@@ -0,0 +1,558 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::format;
+use core::sync::atomic::{AtomicI32, Ordering};
+
+use aster_rights::ReadOp;
+
+use super::KeySerial;
+use crate::{
+    prelude::*,
+    process::{credentials::capabilities::CapSet, Credentials, Gid, Uid},
+};
+
+/// The maximum length of the payloads of `user` and `logon` keys.
+pub const MAX_PAYLOAD_LEN: usize = 32767;
+
+/// The maximum length of the descriptions, excluding the final `\0` byte.
+pub const MAX_DESCRIPTION_LEN: usize = 4095;
+
+/// The maximum depth of nested keyrings that are searched.
+const MAX_SEARCH_DEPTH: usize = 6;
+
+/// The types of keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyType {
+    /// A key whose payload is a blob that can be read by the user space.
+    User,
+    /// A key like [`KeyType::User`], but whose payload can only be used by the kernel.
+    Logon,
+    /// A keyring, whose payload is the links to other keys.
+    Keyring,
+}
+
+impl KeyType {
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "user" => Ok(Self::User),
+            "logon" => Ok(Self::Logon),
+            "keyring" => Ok(Self::Keyring),
+            _ if name.starts_with('.') => {
+                return_errno_with_message!(Errno::EPERM, "the key type is internal")
+            }
+            _ => return_errno_with_message!(Errno::ENODEV, "the key type is not supported"),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::User => "user",
+            Self::Logon => "logon",
+            Self::Keyring => "keyring",
+        }
+    }
+
+    /// Checks whether the description and the payload are valid for the key type.
+    pub fn check(self, description: &str, payload: &[u8]) -> Result<()> {
+        if description.is_empty() {
+            return_errno_with_message!(Errno::EINVAL, "the description is empty");
+        }
+
+        match self {
+            Self::User | Self::Logon => {
+                // The description of a logon key must be prefixed by the service, e.g.,
+                // `cryptsetup:volume`.
+                if self == Self::Logon && description.find(':').is_none_or(|pos| pos == 0) {
+                    return_errno_with_message!(Errno::EINVAL, "the logon key has no prefix");
+                }
+                if payload.len() > MAX_PAYLOAD_LEN {
+                    return_errno_with_message!(Errno::EINVAL, "the payload is too long");
+                }
+            }
+            Self::Keyring => {
+                if !payload.is_empty() {
+                    return_errno_with_message!(Errno::EINVAL, "keyrings have no payloads");
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+bitflags! {
+    /// The permissions to a key for one class of accessors.
+    ///
+    /// The permission mask of a key contains the permissions for the possessors, the owner,
+    /// the group, and the others, from the most significant byte to the least significant byte.
+    pub struct KeyPerm: u32 {
+        /// View the attributes of the key.
+        const VIEW    = 0x01;
+        /// Read the payload of the key, or list the keys in the keyring.
+        const READ    = 0x02;
+        /// Update the payload of the key, or add and remove links in the keyring.
+        const WRITE   = 0x04;
+        /// Find the key, or search in the keyring.
+        const SEARCH  = 0x08;
+        /// Link the key into a keyring.
+        const LINK    = 0x10;
+        /// Change the owner and the permissions of the key.
+        const SETATTR = 0x20;
+        const ALL     = 0x3f;
+    }
+}
+
+const POSSESSOR_SHIFT: u32 = 24;
+const USER_SHIFT: u32 = 16;
+const GROUP_SHIFT: u32 = 8;
+
+/// Builds the permission mask of a key from the permissions for each class.
+pub const fn perm_mask(possessor: KeyPerm, user: KeyPerm, group: KeyPerm, other: KeyPerm) -> u32 {
+    (possessor.bits() << POSSESSOR_SHIFT)
+        | (user.bits() << USER_SHIFT)
+        | (group.bits() << GROUP_SHIFT)
+        | other.bits()
+}
+
+/// The mask of valid permission bits.
+pub const PERM_MASK_ALL: u32 = perm_mask(KeyPerm::ALL, KeyPerm::ALL, KeyPerm::ALL, KeyPerm::ALL);
+
+/// A key, i.e., a typed payload that is identified by its serial number.
+pub struct Key {
+    serial: KeySerial,
+    key_type: KeyType,
+    description: String,
+    inner: Mutex<KeyInner>,
+}
+
+struct KeyInner {
+    uid: Uid,
+    gid: Gid,
+    perm: u32,
+    state: KeyState,
+    payload: Payload,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum KeyState {
+    Valid,
+    Revoked,
+    Invalidated,
+}
+
+enum Payload {
+    Data(Vec<u8>),
+    Keyring(Vec<Arc<Key>>),
+}
+
+impl Key {
+    /// Creates a key and adds it to the table of keys, so it can be looked up by the serial.
+    ///
+    /// The description and the payload should have been checked by [`KeyType::check`].
+    pub fn new(
+        key_type: KeyType,
+        description: String,
+        payload: Vec<u8>,
+        uid: Uid,
+        gid: Gid,
+        perm: u32,
+    ) -> Arc<Self> {
+        let payload = match key_type {
+            KeyType::User | KeyType::Logon => Payload::Data(payload),
+            KeyType::Keyring => Payload::Keyring(Vec::new()),
+        };
+
+        // Serials are positive, and the non-positive values are reserved for the special
+        // keyrings (e.g., `KEY_SPEC_SESSION_KEYRING`).
+        static NEXT_SERIAL: AtomicI32 = AtomicI32::new(1);
+
+        let mut keys = super::KEYS.lock();
+        let serial = loop {
+            let serial = NEXT_SERIAL
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |serial| {
+                    Some(serial.checked_add(1).unwrap_or(1))
+                })
+                .unwrap();
+            if !keys.contains_key(&serial) {
+                break serial;
+            }
+        };
+
+        let key = Arc::new(Self {
+            serial,
+            key_type,
+            description,
+            inner: Mutex::new(KeyInner {
+                uid,
+                gid,
+                perm,
+                state: KeyState::Valid,
+                payload,
+            }),
+        });
+        keys.insert(serial, Arc::downgrade(&key));
+        key
+    }
+
+    /// Creates a keyring that is owned by `uid` and `gid`.
+    pub fn new_keyring(description: String, uid: Uid, gid: Gid, perm: u32) -> Arc<Self> {
+        Self::new(KeyType::Keyring, description, Vec::new(), uid, gid, perm)
+    }
+
+    pub fn serial(&self) -> KeySerial {
+        self.serial
+    }
+
+    pub fn key_type(&self) -> KeyType {
+        self.key_type
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// Returns the UID of the owner.
+    pub fn uid(&self) -> Uid {
+        self.inner.lock().uid
+    }
+
+    pub fn is_keyring(&self) -> bool {
+        self.key_type == KeyType::Keyring
+    }
+
+    /// Returns whether the key can be found by searches.
+    fn is_searchable(&self) -> bool {
+        self.inner.lock().state == KeyState::Valid
+    }
+
+    /// Checks whether the key has been revoked or invalidated.
+    pub fn check_valid(&self) -> Result<()> {
+        match self.inner.lock().state {
+            KeyState::Valid => Ok(()),
+            KeyState::Revoked => {
+                return_errno_with_message!(Errno::EKEYREVOKED, "the key is revoked")
+            }
+            KeyState::Invalidated => {
+                return_errno_with_message!(Errno::ENOKEY, "the key is invalidated")
+            }
+        }
+    }
+
+    /// Checks whether the accessor with `credentials` has all the permissions in `need`.
+    pub fn check_permission(
+        &self,
+        credentials: &Credentials<ReadOp>,
+        possessed: bool,
+        need: KeyPerm,
+    ) -> Result<()> {
+        let inner = self.inner.lock();
+
+        let shift = if inner.uid == credentials.fsuid() {
+            USER_SHIFT
+        } else if inner.gid == credentials.fsgid() || credentials.groups().contains(&inner.gid) {
+            GROUP_SHIFT
+        } else {
+            0
+        };
+        let mut granted = KeyPerm::from_bits_truncate(inner.perm >> shift);
+        if possessed {
+            granted |= KeyPerm::from_bits_truncate(inner.perm >> POSSESSOR_SHIFT);
+        }
+
+        if !granted.contains(need) {
+            return_errno_with_message!(Errno::EACCES, "the key permission is denied");
+        }
+        Ok(())
+    }
+
+    /// Returns the description in the form of `<type>;<uid>;<gid>;<perm>;<description>`.
+    pub fn describe(&self) -> String {
+        let inner = self.inner.lock();
+        format!(
+            "{};{};{};{:08x};{}",
+            self.key_type.name(),
+            u32::from(inner.uid) as i32,
+            u32::from(inner.gid) as i32,
+            inner.perm,
+            self.description
+        )
+    }
+
+    /// Changes the owner and the group of the key.
+    ///
+    /// Only the privileged accessor can change the owner, and the owner can only change the
+    /// group to one of its groups.
+    pub fn chown(
+        &self,
+        credentials: &Credentials<ReadOp>,
+        uid: Option<Uid>,
+        gid: Option<Gid>,
+    ) -> Result<()> {
+        let is_privileged = credentials.effective_capset().contains(CapSet::SYS_ADMIN);
+        let mut inner = self.inner.lock();
+
+        if !is_privileged {
+            if uid.is_some_and(|uid| uid != inner.uid) {
+                return_errno_with_message!(
+                    Errno::EACCES,
+                    "only the privileged can change the owner"
+                );
+            }
+            if let Some(gid) = gid {
+                if inner.uid != credentials.fsuid()
+                    || (gid != credentials.fsgid() && !credentials.groups().contains(&gid))
+                {
+                    return_errno_with_message!(Errno::EACCES, "the group cannot be changed");
+                }
+            }
+        }
+
+        if let Some(uid) = uid {
+            inner.uid = uid;
+        }
+        if let Some(gid) = gid {
+            inner.gid = gid;
+        }
+        Ok(())
+    }
+
+    /// Sets the permission mask of the key.
+    pub fn set_perm(&self, credentials: &Credentials<ReadOp>, perm: u32) -> Result<()> {
+        if perm & !PERM_MASK_ALL != 0 {
+            return_errno_with_message!(Errno::EINVAL, "the permission mask is invalid");
+        }
+
+        let is_privileged = credentials.effective_capset().contains(CapSet::SYS_ADMIN);
+        let mut inner = self.inner.lock();
+        if !is_privileged && inner.uid != credentials.fsuid() {
+            return_errno_with_message!(Errno::EACCES, "only the owner can set the permissions");
+        }
+        inner.perm = perm;
+        Ok(())
+    }
+
+    /// Replaces the payload of the key.
+    pub fn update(&self, payload: Vec<u8>) -> Result<()> {
+        self.key_type.check(&self.description, &payload)?;
+
+        let mut inner = self.inner.lock();
+        match &mut inner.payload {
+            Payload::Data(data) => {
+                data.fill(0);
+                *data = payload;
+                Ok(())
+            }
+            Payload::Keyring(_) => {
+                return_errno_with_message!(Errno::EOPNOTSUPP, "keyrings cannot be updated")
+            }
+        }
+    }
+
+    /// Reads the payload of the key for the user space.
+    ///
+    /// The payload of a keyring is the array of the serials of the linked keys.
+    pub fn read(&self) -> Result<Vec<u8>> {
+        if self.key_type == KeyType::Logon {
+            return_errno_with_message!(Errno::EOPNOTSUPP, "logon keys cannot be read");
+        }
+
+        let inner = self.inner.lock();
+        match &inner.payload {
+            Payload::Data(data) => Ok(data.clone()),
+            Payload::Keyring(links) => Ok(links
+                .iter()
+                .flat_map(|key| key.serial.to_ne_bytes())
+                .collect()),
+        }
+    }
+
+    /// Returns the payload of a `user` or `logon` key for the kernel.
+    pub fn payload(&self) -> Option<Vec<u8>> {
+        let inner = self.inner.lock();
+        match &inner.payload {
+            Payload::Data(data) if inner.state == KeyState::Valid => Some(data.clone()),
+            _ => None,
+        }
+    }
+
+    /// Revokes the key, after which the key cannot be used anymore.
+    pub fn revoke(&self) {
+        let mut inner = self.inner.lock();
+        if inner.state == KeyState::Valid {
+            inner.state = KeyState::Revoked;
+            inner.payload.clear();
+        }
+    }
+
+    /// Invalidates the key, which also removes the key from the table of keys.
+    ///
+    /// The links to the key in the keyrings are removed lazily.
+    pub fn invalidate(&self) {
+        {
+            let mut inner = self.inner.lock();
+            inner.state = KeyState::Invalidated;
+            inner.payload.clear();
+        }
+        super::KEYS.lock().remove(&self.serial);
+    }
+
+    // ******** Keyring methods ********
+
+    /// Returns the keys linked in the keyring.
+    pub fn links(&self) -> Result<Vec<Arc<Key>>> {
+        let mut inner = self.inner.lock();
+        let Payload::Keyring(links) = &mut inner.payload else {
+            return_errno_with_message!(Errno::ENOTDIR, "the key is not a keyring");
+        };
+
+        links.retain(|key| key.inner.lock().state != KeyState::Invalidated);
+        Ok(links.clone())
+    }
+
+    /// Links `key` into the keyring.
+    ///
+    /// If a key of the same type and description has been linked, the link is replaced.
+    pub fn link(self: &Arc<Self>, key: Arc<Key>) -> Result<()> {
+        if !self.is_keyring() {
+            return_errno_with_message!(Errno::ENOTDIR, "the key is not a keyring");
+        }
+
+        // Serialize the links of keyrings, so that concurrent links cannot make a cycle
+        // that is not detected.
+        static KEYRING_LINK_LOCK: Mutex<()> = Mutex::new(());
+        let _guard = key.is_keyring().then(|| KEYRING_LINK_LOCK.lock());
+        if key.is_keyring() && key.reaches(self) {
+            return_errno_with_message!(Errno::EDEADLK, "the link makes a cycle");
+        }
+
+        let mut inner = self.inner.lock();
+        let Payload::Keyring(links) = &mut inner.payload else {
+            return_errno_with_message!(Errno::EKEYREVOKED, "the keyring is revoked");
+        };
+
+        links.retain(|linked| {
+            linked.key_type != key.key_type || linked.description != key.description
+        });
+        links.push(key);
+        Ok(())
+    }
+
+    /// Removes the link to `key` from the keyring.
+    pub fn unlink(&self, key: &Arc<Key>) -> Result<()> {
+        let mut inner = self.inner.lock();
+        let Payload::Keyring(links) = &mut inner.payload else {
+            return_errno_with_message!(Errno::ENOTDIR, "the key is not a keyring");
+        };
+
+        let Some(pos) = links.iter().position(|linked| Arc::ptr_eq(linked, key)) else {
+            return_errno_with_message!(Errno::ENOENT, "the key is not linked in the keyring");
+        };
+        links.remove(pos);
+        Ok(())
+    }
+
+    /// Removes all the links from the keyring.
+    pub fn clear(&self) -> Result<()> {
+        let links = {
+            let mut inner = self.inner.lock();
+            let Payload::Keyring(links) = &mut inner.payload else {
+                return_errno_with_message!(Errno::ENOTDIR, "the key is not a keyring");
+            };
+            core::mem::take(links)
+        };
+        // The keys are dropped here, after releasing the lock.
+        drop(links);
+        Ok(())
+    }
+
+    /// Returns whether `target` is the keyring itself or is linked (recursively) from it.
+    fn reaches(self: &Arc<Self>, target: &Arc<Key>) -> bool {
+        let mut stack = vec![self.clone()];
+        let mut visited = BTreeSet::new();
+        while let Some(keyring) = stack.pop() {
+            if Arc::ptr_eq(&keyring, target) {
+                return true;
+            }
+            if !visited.insert(keyring.serial) {
+                continue;
+            }
+            if let Ok(links) = keyring.links() {
+                stack.extend(links.into_iter().filter(|key| key.is_keyring()));
+            }
+        }
+        false
+    }
+
+    /// Searches the keyring and the nested keyrings for the key that matches `is_target`.
+    ///
+    /// The nested keyrings are searched only if the accessor has the search permission.
+    /// The found key is also required to have the search permission unless
+    /// `check_target_perm` is false.
+    pub fn search(
+        self: &Arc<Self>,
+        credentials: &Credentials<ReadOp>,
+        possessed: bool,
+        check_target_perm: bool,
+        is_target: &dyn Fn(&Key) -> bool,
+    ) -> Option<Arc<Key>> {
+        let mut stack = vec![(self.clone(), 0)];
+        let mut visited = BTreeSet::new();
+        while let Some((keyring, depth)) = stack.pop() {
+            if !visited.insert(keyring.serial) {
+                continue;
+            }
+            let Ok(links) = keyring.links() else {
+                continue;
+            };
+
+            for key in links {
+                if !key.is_searchable() {
+                    continue;
+                }
+                if is_target(&key)
+                    && (!check_target_perm
+                        || key
+                            .check_permission(credentials, possessed, KeyPerm::SEARCH)
+                            .is_ok())
+                {
+                    return Some(key);
+                }
+                if key.is_keyring()
+                    && depth + 1 < MAX_SEARCH_DEPTH
+                    && key
+                        .check_permission(credentials, possessed, KeyPerm::SEARCH)
+                        .is_ok()
+                {
+                    stack.push((key, depth + 1));
+                }
+            }
+        }
+        None
+    }
+}
+
+impl Drop for Key {
+    fn drop(&mut self) {
+        // The payload may contain secrets.
+        self.inner.get_mut().payload.clear();
+
+        let mut keys = super::KEYS.lock();
+        if keys
+            .get(&self.serial)
+            .is_some_and(|key| key.strong_count() == 0)
+        {
+            keys.remove(&self.serial);
+        }
+    }
+}
+
+impl Payload {
+    fn clear(&mut self) {
+        match self {
+            Self::Data(data) => {
+                data.fill(0);
+                data.clear();
+            }
+            Self::Keyring(links) => links.clear(),
+        }
+    }
+}
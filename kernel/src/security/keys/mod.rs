@@ -0,0 +1,396 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The key retention service.
+//!
+//! A key is a typed payload (e.g., the key of an encrypted volume) that is identified by its
+//! serial number, and a keyring is a key whose payload is the links to other keys. Each thread
+//! is associated with a thread keyring, a process keyring shared by all threads of the
+//! process, and a session keyring inherited by the children. Each user has a user keyring and a
+//! user session keyring, where the latter is used if the thread has not joined a session.
+//!
+//! The keys that are reachable from the keyrings of a thread are possessed by the thread, which
+//! then gets the possessor permissions of the keys (see [`KeyPerm`]).
+//!
+//! Only the `user`, `logon`, and `keyring` key types are supported. The keys cannot be
+//! constructed by calling out to `/sbin/request-key`, so `request_key` only finds the keys that
+//! have been added.
+//!
+//! Reference: <https://www.kernel.org/doc/html/latest/security/keys/core.html>
+
+mod key;
+
+use alloc::format;
+
+use aster_rights::ReadOp;
+pub use key::{perm_mask, Key, KeyPerm, KeyType, MAX_DESCRIPTION_LEN};
+
+use crate::{
+    prelude::*,
+    process::{
+        posix_thread::{AsPosixThread, PosixThread},
+        Credentials, Gid,
+    },
+    thread::Thread,
+};
+
+pub type KeySerial = i32;
+
+// The special serials that refer to the keyrings of the current thread.
+
+pub const KEY_SPEC_THREAD_KEYRING: KeySerial = -1;
+pub const KEY_SPEC_PROCESS_KEYRING: KeySerial = -2;
+pub const KEY_SPEC_SESSION_KEYRING: KeySerial = -3;
+pub const KEY_SPEC_USER_KEYRING: KeySerial = -4;
+pub const KEY_SPEC_USER_SESSION_KEYRING: KeySerial = -5;
+pub const KEY_SPEC_GROUP_KEYRING: KeySerial = -6;
+pub const KEY_SPEC_REQKEY_AUTH_KEY: KeySerial = -7;
+pub const KEY_SPEC_REQUESTOR_KEYRING: KeySerial = -8;
+
+/// The table of all keys, indexed by their serials.
+static KEYS: Mutex<BTreeMap<KeySerial, Weak<Key>>> = Mutex::new(BTreeMap::new());
+
+/// The user keyrings and the user session keyrings, indexed by the UIDs.
+static USER_KEYRINGS: Mutex<BTreeMap<u32, (Arc<Key>, Arc<Key>)>> = Mutex::new(BTreeMap::new());
+
+/// The permissions of the keyrings that are created for threads, processes, and sessions.
+const THREAD_KEYRING_PERM: u32 = perm_mask(
+    KeyPerm::ALL,
+    KeyPerm::VIEW,
+    KeyPerm::empty(),
+    KeyPerm::empty(),
+);
+const SESSION_KEYRING_PERM: u32 = perm_mask(
+    KeyPerm::ALL,
+    KeyPerm::VIEW.union(KeyPerm::READ).union(KeyPerm::LINK),
+    KeyPerm::empty(),
+    KeyPerm::empty(),
+);
+const USER_KEYRING_PERM: u32 = perm_mask(
+    KeyPerm::ALL,
+    KeyPerm::ALL,
+    KeyPerm::empty(),
+    KeyPerm::empty(),
+);
+
+pub(crate) fn init() {
+    aster_dm::set_key_lookup(lookup_payload_for_dm);
+}
+
+/// The keyrings of a thread.
+pub struct ThreadKeyrings {
+    thread: Mutex<Option<Arc<Key>>>,
+    process: Arc<Mutex<Option<Arc<Key>>>>,
+    session: Mutex<Option<Arc<Key>>>,
+}
+
+impl ThreadKeyrings {
+    /// Creates the keyrings of the first thread, which are all empty.
+    pub fn new() -> Self {
+        Self {
+            thread: Mutex::new(None),
+            process: Arc::new(Mutex::new(None)),
+            session: Mutex::new(None),
+        }
+    }
+
+    /// Creates the keyrings of a new thread that is cloned from this thread.
+    ///
+    /// The session keyring is inherited, and the process keyring is shared only if the new
+    /// thread belongs to the same process.
+    pub fn new_child(&self, is_same_process: bool) -> Self {
+        let process = if is_same_process {
+            self.process.clone()
+        } else {
+            Arc::new(Mutex::new(None))
+        };
+
+        Self {
+            thread: Mutex::new(None),
+            process,
+            session: Mutex::new(self.session.lock().clone()),
+        }
+    }
+
+    /// Discards the thread keyring and the process keyring when executing a new program.
+    pub fn on_exec(&self) {
+        *self.thread.lock() = None;
+        *self.process.lock() = None;
+    }
+}
+
+impl Default for ThreadKeyrings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+bitflags! {
+    /// The flags to look up a key by its serial.
+    pub struct KeyLookupFlags: u32 {
+        /// Create the keyring of the thread if it does not exist.
+        const CREATE = 1 << 0;
+        /// Allow the key to be revoked.
+        const PARTIAL = 1 << 1;
+    }
+}
+
+/// A key that is looked up by a thread.
+pub struct KeyRef {
+    key: Arc<Key>,
+    possessed: bool,
+}
+
+impl KeyRef {
+    pub fn key(&self) -> &Arc<Key> {
+        &self.key
+    }
+
+    /// Returns whether the key is possessed by the thread that looks it up.
+    pub fn is_possessed(&self) -> bool {
+        self.possessed
+    }
+
+    /// Checks whether the thread with `credentials` has all the permissions in `need`.
+    pub fn check_permission(&self, credentials: &Credentials<ReadOp>, need: KeyPerm) -> Result<()> {
+        self.key.check_permission(credentials, self.possessed, need)
+    }
+
+    /// Searches the keyring for the key of `key_type` and `description`.
+    pub fn search(
+        &self,
+        credentials: &Credentials<ReadOp>,
+        key_type: KeyType,
+        description: &str,
+    ) -> Option<KeyRef> {
+        let key = self.key.search(credentials, self.possessed, true, &|key| {
+            key.key_type() == key_type && key.description() == description
+        })?;
+        Some(KeyRef {
+            key,
+            possessed: self.possessed,
+        })
+    }
+}
+
+/// Looks up a key by its serial, which may be one of the special serials (e.g.,
+/// [`KEY_SPEC_SESSION_KEYRING`]).
+pub fn lookup_key(
+    posix_thread: &PosixThread,
+    serial: KeySerial,
+    flags: KeyLookupFlags,
+) -> Result<KeyRef> {
+    let keyrings = posix_thread.keyrings();
+    let credentials = posix_thread.credentials();
+    let create = flags.contains(KeyLookupFlags::CREATE);
+
+    let get_or_create = |keyring: &Mutex<Option<Arc<Key>>>, name: &str| -> Result<Arc<Key>> {
+        let mut keyring = keyring.lock();
+        if let Some(keyring) = keyring.as_ref() {
+            return Ok(keyring.clone());
+        }
+        if !create {
+            return_errno_with_message!(Errno::ENOKEY, "the keyring does not exist");
+        }
+        let new_keyring = Key::new_keyring(
+            name.to_string(),
+            credentials.fsuid(),
+            credentials.fsgid(),
+            THREAD_KEYRING_PERM,
+        );
+        *keyring = Some(new_keyring.clone());
+        Ok(new_keyring)
+    };
+
+    let key = match serial {
+        KEY_SPEC_THREAD_KEYRING => get_or_create(&keyrings.thread, "_tid")?,
+        KEY_SPEC_PROCESS_KEYRING => get_or_create(&keyrings.process, "_pid")?,
+        KEY_SPEC_SESSION_KEYRING => {
+            let session = keyrings.session.lock().clone();
+            match session {
+                Some(session) => session,
+                None if create => join_session_keyring(posix_thread, None)?,
+                None => user_keyrings(&credentials).1,
+            }
+        }
+        KEY_SPEC_USER_KEYRING => user_keyrings(&credentials).0,
+        KEY_SPEC_USER_SESSION_KEYRING => user_keyrings(&credentials).1,
+        KEY_SPEC_GROUP_KEYRING => {
+            return_errno_with_message!(Errno::EINVAL, "group keyrings are not supported")
+        }
+        KEY_SPEC_REQKEY_AUTH_KEY | KEY_SPEC_REQUESTOR_KEYRING => {
+            return_errno_with_message!(Errno::ENOKEY, "the thread is not constructing a key")
+        }
+        serial if serial <= 0 => {
+            return_errno_with_message!(Errno::EINVAL, "the special serial is invalid")
+        }
+        serial => {
+            let key = KEYS.lock().get(&serial).and_then(Weak::upgrade);
+            let key =
+                key.ok_or_else(|| Error::with_message(Errno::ENOKEY, "the key does not exist"))?;
+            let possessed = is_possessed(posix_thread, &credentials, &key);
+            let key_ref = KeyRef { key, possessed };
+            check_state(&key_ref, flags)?;
+            return Ok(key_ref);
+        }
+    };
+
+    // The keyrings of the thread are always possessed by the thread.
+    let key_ref = KeyRef {
+        key,
+        possessed: true,
+    };
+    check_state(&key_ref, flags)?;
+    Ok(key_ref)
+}
+
+fn check_state(key_ref: &KeyRef, flags: KeyLookupFlags) -> Result<()> {
+    match key_ref.key.check_valid() {
+        Err(err)
+            if err.error() == Errno::EKEYREVOKED && flags.contains(KeyLookupFlags::PARTIAL) =>
+        {
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+/// Returns the keyrings that the thread possesses directly.
+fn possessed_keyrings(
+    posix_thread: &PosixThread,
+    credentials: &Credentials<ReadOp>,
+) -> Vec<Arc<Key>> {
+    let keyrings = posix_thread.keyrings();
+
+    let mut roots = Vec::with_capacity(3);
+    roots.extend(keyrings.thread.lock().clone());
+    roots.extend(keyrings.process.lock().clone());
+    let session = keyrings.session.lock().clone();
+    roots.push(session.unwrap_or_else(|| user_keyrings(credentials).1));
+    roots
+}
+
+fn is_possessed(
+    posix_thread: &PosixThread,
+    credentials: &Credentials<ReadOp>,
+    key: &Arc<Key>,
+) -> bool {
+    possessed_keyrings(posix_thread, credentials)
+        .iter()
+        .any(|keyring| {
+            Arc::ptr_eq(keyring, key)
+                || keyring
+                    .search(credentials, true, false, &|target| {
+                        core::ptr::eq(target, key.as_ref())
+                    })
+                    .is_some()
+        })
+}
+
+/// Searches the thread, process, and session keyrings for the key of `key_type` and
+/// `description`.
+pub fn search_process_keyrings(
+    posix_thread: &PosixThread,
+    key_type: KeyType,
+    description: &str,
+) -> Option<KeyRef> {
+    let credentials = posix_thread.credentials();
+
+    possessed_keyrings(posix_thread, &credentials)
+        .into_iter()
+        .find_map(|keyring| {
+            KeyRef {
+                key: keyring,
+                possessed: true,
+            }
+            .search(&credentials, key_type, description)
+        })
+}
+
+/// Joins the session keyring of `name`, or a new anonymous session keyring if `name` is `None`.
+///
+/// If no session keyring of `name` can be found, a new one is created.
+pub fn join_session_keyring(posix_thread: &PosixThread, name: Option<&str>) -> Result<Arc<Key>> {
+    let credentials = posix_thread.credentials();
+    let mut session = posix_thread.keyrings().session.lock();
+
+    let keyring = if let Some(name) = name {
+        if let Some(session) = session
+            .as_ref()
+            .filter(|session| session.description() == name)
+        {
+            return Ok(session.clone());
+        }
+        find_keyring_by_name(&credentials, name).unwrap_or_else(|| {
+            Key::new_keyring(
+                name.to_string(),
+                credentials.fsuid(),
+                credentials.fsgid(),
+                SESSION_KEYRING_PERM,
+            )
+        })
+    } else {
+        Key::new_keyring(
+            "_ses".to_string(),
+            credentials.fsuid(),
+            credentials.fsgid(),
+            SESSION_KEYRING_PERM,
+        )
+    };
+
+    *session = Some(keyring.clone());
+    Ok(keyring)
+}
+
+/// Finds a keyring of the user by its name, which the thread can search.
+fn find_keyring_by_name(credentials: &Credentials<ReadOp>, name: &str) -> Option<Arc<Key>> {
+    let keys: Vec<Arc<Key>> = KEYS.lock().values().filter_map(Weak::upgrade).collect();
+
+    keys.into_iter().find(|key| {
+        key.is_keyring()
+            && key.description() == name
+            && key.check_valid().is_ok()
+            && key.uid() == credentials.fsuid()
+            && key
+                .check_permission(credentials, false, KeyPerm::SEARCH)
+                .is_ok()
+    })
+}
+
+/// Returns the user keyring and the user session keyring of the real user.
+fn user_keyrings(credentials: &Credentials<ReadOp>) -> (Arc<Key>, Arc<Key>) {
+    let uid = credentials.ruid();
+
+    let mut user_keyrings = USER_KEYRINGS.lock();
+    user_keyrings
+        .entry(uid.into())
+        .or_insert_with(|| {
+            let uid_num = u32::from(uid);
+            let no_gid = Gid::new(u32::MAX);
+
+            let user_keyring =
+                Key::new_keyring(format!("_uid.{}", uid_num), uid, no_gid, USER_KEYRING_PERM);
+            let user_session_keyring = Key::new_keyring(
+                format!("_uid_ses.{}", uid_num),
+                uid,
+                no_gid,
+                USER_KEYRING_PERM,
+            );
+            // The user session keyring is linked with the user keyring, so the keys in the user
+            // keyring are also possessed by the threads without sessions.
+            user_session_keyring.link(user_keyring.clone()).unwrap();
+
+            (user_keyring, user_session_keyring)
+        })
+        .clone()
+}
+
+/// Looks up the payload of a `user` or `logon` key in the keyrings of the current thread.
+fn lookup_payload_for_dm(key_type: &str, description: &str) -> Option<Vec<u8>> {
+    let key_type = KeyType::from_name(key_type).ok()?;
+
+    let current = Thread::current()?;
+    let posix_thread = current.as_posix_thread()?;
+    search_process_keyrings(posix_thread, key_type, description)?
+        .key()
+        .payload()
+}
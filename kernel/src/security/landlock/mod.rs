@@ -0,0 +1,281 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The Landlock security module, with which threads can restrict their own access to files.
+//!
+//! A thread creates a ruleset that handles some kinds of file access, adds the rules that allow
+//! some of the handled access beneath some files, and then enforces the ruleset on itself. The
+//! enforced rulesets form the Landlock domain of the thread, which is inherited by the children
+//! and can never be lifted. An access is allowed only if every ruleset in the domain that handles
+//! the access has a rule allowing it on the file or on one of the ancestors of the file.
+//!
+//! Only the file system access of the first Landlock ABI version is supported. Without
+//! `LANDLOCK_ACCESS_FS_REFER` of the later versions, files cannot be linked or renamed to another
+//! directory by a restricted thread.
+//!
+//! Reference: <https://docs.kernel.org/userspace-api/landlock.html>
+
+mod ruleset;
+
+pub use ruleset::LandlockRuleset;
+
+use super::hooks::{self, SecurityModule};
+use crate::{
+    fs::{
+        path::Dentry,
+        utils::{AccessMode, InodeType},
+    },
+    prelude::*,
+    process::posix_thread::AsPosixThread,
+    thread::Thread,
+};
+
+/// The supported version of the Landlock ABI.
+pub const LANDLOCK_ABI_VERSION: u32 = 1;
+
+/// The maximum number of the rulesets that are enforced on a thread.
+const MAX_NUM_LAYERS: usize = 16;
+
+pub(super) fn init() {
+    hooks::register_module(Arc::new(Landlock));
+}
+
+bitflags! {
+    /// The kinds of file system access that are handled by Landlock.
+    pub struct AccessFs: u64 {
+        /// Execute a file.
+        const EXECUTE = 1 << 0;
+        /// Open a file with write access.
+        const WRITE_FILE = 1 << 1;
+        /// Open a file with read access.
+        const READ_FILE = 1 << 2;
+        /// Open a directory or list its content.
+        const READ_DIR = 1 << 3;
+        /// Remove an empty directory or rename one.
+        const REMOVE_DIR = 1 << 4;
+        /// Unlink or rename a file.
+        const REMOVE_FILE = 1 << 5;
+        /// Create, rename, or link a character device.
+        const MAKE_CHAR = 1 << 6;
+        /// Create or rename a directory.
+        const MAKE_DIR = 1 << 7;
+        /// Create, rename, or link a regular file.
+        const MAKE_REG = 1 << 8;
+        /// Create, rename, or link a UNIX domain socket.
+        const MAKE_SOCK = 1 << 9;
+        /// Create, rename, or link a named pipe.
+        const MAKE_FIFO = 1 << 10;
+        /// Create, rename, or link a block device.
+        const MAKE_BLOCK = 1 << 11;
+        /// Create, rename, or link a symbolic link.
+        const MAKE_SYM = 1 << 12;
+    }
+}
+
+impl AccessFs {
+    /// The access that can be allowed on the files that are not directories.
+    const FILE: Self = Self::EXECUTE.union(Self::WRITE_FILE).union(Self::READ_FILE);
+
+    /// Returns the access that is needed to make a file of the type in a directory.
+    fn make(type_: InodeType) -> Self {
+        match type_ {
+            InodeType::Dir => Self::MAKE_DIR,
+            InodeType::SymLink => Self::MAKE_SYM,
+            InodeType::CharDevice => Self::MAKE_CHAR,
+            InodeType::BlockDevice => Self::MAKE_BLOCK,
+            InodeType::NamedPipe => Self::MAKE_FIFO,
+            InodeType::Socket => Self::MAKE_SOCK,
+            _ => Self::MAKE_REG,
+        }
+    }
+
+    /// Returns the access that is needed to remove a file of the type from a directory.
+    fn remove(type_: InodeType) -> Self {
+        if type_ == InodeType::Dir {
+            Self::REMOVE_DIR
+        } else {
+            Self::REMOVE_FILE
+        }
+    }
+}
+
+/// The rulesets that are enforced on a thread.
+#[derive(Debug, Default)]
+pub struct LandlockDomain {
+    layers: Vec<Arc<Layer>>,
+}
+
+/// A ruleset that has been enforced, whose rules are fixed.
+#[derive(Debug)]
+struct Layer {
+    handled_access: AccessFs,
+    rules: BTreeMap<RuleKey, Rule>,
+}
+
+/// The key of a rule, which identifies the file that the rule is added beneath.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct RuleKey {
+    fs: usize,
+    ino: u64,
+}
+
+impl RuleKey {
+    fn new(dentry: &Dentry) -> Self {
+        Self {
+            fs: Arc::as_ptr(&dentry.fs()) as *const () as usize,
+            ino: dentry.inode().ino(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    /// The file, which is kept so that its inode number is not reused.
+    _dentry: Dentry,
+    allowed_access: AccessFs,
+}
+
+impl LandlockDomain {
+    /// Returns a new domain with the ruleset enforced on top of this domain.
+    pub fn with_ruleset(&self, ruleset: &LandlockRuleset) -> Result<Self> {
+        if self.layers.len() >= MAX_NUM_LAYERS {
+            return_errno_with_message!(Errno::E2BIG, "too many rulesets are enforced");
+        }
+
+        let mut layers = self.layers.clone();
+        layers.push(Arc::new(ruleset.to_layer()));
+        Ok(Self { layers })
+    }
+
+    /// Checks whether the access to the file is allowed by every layer that handles it.
+    fn check(&self, dentry: &Dentry, access: AccessFs) -> Result<()> {
+        for layer in self.layers.iter() {
+            let mut denied_access = access & layer.handled_access;
+
+            let mut ancestor = Some(dentry.clone());
+            while let Some(dentry) = ancestor {
+                if denied_access.is_empty() {
+                    break;
+                }
+                if let Some(rule) = layer.rules.get(&RuleKey::new(&dentry)) {
+                    denied_access -= rule.allowed_access;
+                }
+                ancestor = dentry.effective_parent();
+            }
+
+            if !denied_access.is_empty() {
+                return_errno_with_message!(Errno::EACCES, "the access is denied by Landlock");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the Landlock domain of the current thread, if any ruleset has been enforced.
+fn current_domain() -> Option<Arc<LandlockDomain>> {
+    let current = Thread::current()?;
+    let posix_thread = current.as_posix_thread()?;
+    posix_thread.credentials().landlock_domain()
+}
+
+/// Checks whether the current thread can access the file.
+fn check_access(dentry: &Dentry, access: AccessFs) -> Result<()> {
+    let Some(domain) = current_domain() else {
+        return Ok(());
+    };
+    domain.check(dentry, access)
+}
+
+/// The Landlock security module, which enforces the domain of the current thread.
+#[derive(Debug)]
+struct Landlock;
+
+impl SecurityModule for Landlock {
+    fn name(&self) -> &'static str {
+        "landlock"
+    }
+
+    fn bprm_check(&self, dentry: &Dentry) -> Result<()> {
+        check_access(dentry, AccessFs::EXECUTE)
+    }
+
+    fn file_open(&self, dentry: &Dentry, access_mode: AccessMode) -> Result<()> {
+        let mut access = AccessFs::empty();
+        if dentry.type_() == InodeType::Dir {
+            access |= AccessFs::READ_DIR;
+        } else {
+            if access_mode.is_readable() {
+                access |= AccessFs::READ_FILE;
+            }
+            if access_mode.is_writable() {
+                access |= AccessFs::WRITE_FILE;
+            }
+        }
+        check_access(dentry, access)
+    }
+
+    fn inode_create(&self, dir: &Dentry, type_: InodeType) -> Result<()> {
+        check_access(dir, AccessFs::make(type_))
+    }
+
+    fn inode_link(&self, old: &Dentry, new_dir: &Dentry) -> Result<()> {
+        let Some(domain) = current_domain() else {
+            return Ok(());
+        };
+
+        let old_dir = old
+            .effective_parent()
+            .ok_or_else(|| Error::with_message(Errno::EPERM, "the root cannot be linked"))?;
+        check_same_directory(&old_dir, new_dir)?;
+        domain.check(new_dir, AccessFs::make(old.type_()))
+    }
+
+    fn inode_unlink(&self, dir: &Dentry, _name: &str) -> Result<()> {
+        check_access(dir, AccessFs::REMOVE_FILE)
+    }
+
+    fn inode_rmdir(&self, dir: &Dentry, _name: &str) -> Result<()> {
+        check_access(dir, AccessFs::REMOVE_DIR)
+    }
+
+    fn inode_rename(
+        &self,
+        old_dir: &Dentry,
+        old_name: &str,
+        new_dir: &Dentry,
+        new_name: &str,
+        is_exchange: bool,
+    ) -> Result<()> {
+        let Some(domain) = current_domain() else {
+            return Ok(());
+        };
+
+        check_same_directory(old_dir, new_dir)?;
+
+        let old = old_dir.lookup(old_name)?;
+        let new = new_dir.lookup(new_name).ok();
+        let mut old_dir_access = AccessFs::remove(old.type_());
+        let mut new_dir_access = AccessFs::make(old.type_());
+        if let Some(new) = new {
+            new_dir_access |= AccessFs::remove(new.type_());
+            if is_exchange {
+                old_dir_access |= AccessFs::make(new.type_());
+            }
+        }
+
+        domain.check(old_dir, old_dir_access)?;
+        domain.check(new_dir, new_dir_access)
+    }
+}
+
+/// Checks that the file stays in its directory, since `LANDLOCK_ACCESS_FS_REFER` of the later
+/// Landlock ABI versions is not supported.
+fn check_same_directory(old_dir: &Dentry, new_dir: &Dentry) -> Result<()> {
+    if old_dir.key() != new_dir.key() {
+        return_errno_with_message!(
+            Errno::EXDEV,
+            "the file cannot be moved to another directory with Landlock"
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,105 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{AccessFs, Layer, Rule, RuleKey};
+use crate::{
+    events::IoEvents,
+    fs::{
+        file_handle::FileLike,
+        path::Dentry,
+        utils::{InodeMode, InodeType, Metadata},
+    },
+    prelude::*,
+    process::{
+        signal::{PollHandle, Pollable},
+        Gid, Uid,
+    },
+    time::clocks::RealTimeClock,
+};
+
+/// A Landlock ruleset, which collects the rules before it is enforced.
+///
+/// The ruleset is referred to by a file descriptor in the user space.
+pub struct LandlockRuleset {
+    handled_access: AccessFs,
+    rules: Mutex<BTreeMap<RuleKey, Rule>>,
+}
+
+impl LandlockRuleset {
+    /// Creates an empty ruleset that handles the access.
+    pub fn new(handled_access: AccessFs) -> Result<Self> {
+        if handled_access.is_empty() {
+            return_errno_with_message!(Errno::ENOMSG, "the ruleset handles no access");
+        }
+
+        Ok(Self {
+            handled_access,
+            rules: Mutex::new(BTreeMap::new()),
+        })
+    }
+
+    /// Adds a rule that allows the access beneath the file.
+    ///
+    /// If there is already a rule for the file, the allowed access is merged.
+    pub fn add_path_beneath_rule(&self, dentry: Dentry, allowed_access: AccessFs) -> Result<()> {
+        if allowed_access.is_empty() {
+            return_errno_with_message!(Errno::ENOMSG, "the rule allows no access");
+        }
+        if !self.handled_access.contains(allowed_access) {
+            return_errno_with_message!(Errno::EINVAL, "the allowed access is not handled");
+        }
+        if dentry.type_() != InodeType::Dir && !AccessFs::FILE.contains(allowed_access) {
+            return_errno_with_message!(
+                Errno::EINVAL,
+                "the allowed access does not apply to files that are not directories"
+            );
+        }
+
+        let mut rules = self.rules.lock();
+        rules
+            .entry(RuleKey::new(&dentry))
+            .and_modify(|rule| rule.allowed_access |= allowed_access)
+            .or_insert(Rule {
+                _dentry: dentry,
+                allowed_access,
+            });
+        Ok(())
+    }
+
+    pub(super) fn to_layer(&self) -> Layer {
+        Layer {
+            handled_access: self.handled_access,
+            rules: self.rules.lock().clone(),
+        }
+    }
+}
+
+impl Pollable for LandlockRuleset {
+    fn poll(&self, _mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        IoEvents::empty()
+    }
+}
+
+impl FileLike for LandlockRuleset {
+    fn metadata(&self) -> Metadata {
+        // This is a dummy implementation.
+        // TODO: Add "anonymous inode fs" and link `LandlockRuleset` to it.
+        let now = RealTimeClock::get().read_time();
+        Metadata {
+            dev: 0,
+            ino: 0,
+            size: 0,
+            blk_size: 0,
+            blocks: 0,
+            atime: now,
+            mtime: now,
+            ctime: now,
+            btime: None,
+            type_: InodeType::File,
+            mode: InodeMode::from_bits_truncate(0o600),
+            nlinks: 1,
+            uid: Uid::new_root(),
+            gid: Gid::new_root(),
+            rdev: 0,
+        }
+    }
+}
@@ -0,0 +1,13 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The security facilities that are not tied to a specific subsystem.
+
+pub mod hooks;
+pub mod keys;
+pub mod landlock;
+pub mod seccomp;
+
+pub(super) fn init() {
+    keys::init();
+    landlock::init();
+}
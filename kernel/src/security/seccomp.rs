@@ -0,0 +1,387 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Secure computing (seccomp), with which threads can restrict the system calls they make.
+//!
+//! In the strict mode, a thread can only read and write the opened files, return from the
+//! signal handlers, and exit. In the filter mode, each system call is checked by the classic
+//! BPF filters that are installed by the thread, which are run in the eBPF interpreter after
+//! being converted (see [`crate::bpf::cbpf`]). The filters are inherited by the children and
+//! can never be removed. If several filters are installed, all of them are run, and the most
+//! restrictive action is taken.
+//!
+//! Unlike Linux, the filters cannot notify a tracer or a supervisor, so the system calls are
+//! failed with `ENOSYS` for the `SECCOMP_RET_TRACE` and `SECCOMP_RET_USER_NOTIF` actions, as
+//! if there were no tracers or supervisors.
+//!
+//! The actions are audited in the kernel log like Linux does without the audit daemon. The
+//! `SECCOMP_RET_KILL_*` and `SECCOMP_RET_LOG` actions are always audited, while the other
+//! actions other than `SECCOMP_RET_ALLOW` are audited only if the filter that takes the action
+//! is installed with `SECCOMP_FILTER_FLAG_LOG`. In both cases, the action must be in
+//! `/proc/sys/kernel/seccomp/actions_logged`.
+//!
+//! Reference: <https://docs.kernel.org/userspace-api/seccomp_filter.html>
+
+use core::{
+    fmt,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use crate::{
+    bpf::{
+        cbpf::{self, SockFilter},
+        BpfProg, BpfProgType,
+    },
+    prelude::*,
+    process::{
+        posix_thread::{do_exit, do_exit_group},
+        signal::{
+            constants::{SIGKILL, SIGSYS, SYS_SECCOMP},
+            sig_num::SigNum,
+            signals::syscall::SyscallSignal,
+        },
+        TermStatus,
+    },
+};
+
+/// The seccomp mode of a thread.
+#[derive(Debug, Clone, Default)]
+pub enum SeccompMode {
+    /// The system calls are not restricted.
+    #[default]
+    Disabled,
+    /// Only `read`, `write`, `exit` and `rt_sigreturn` are allowed.
+    Strict,
+    /// The system calls are checked by the filters, the last installed of which is kept here.
+    Filter(Arc<SeccompFilter>),
+}
+
+impl SeccompMode {
+    /// Returns the value of the mode in `prctl(PR_GET_SECCOMP)` and `/proc/[pid]/status`.
+    pub fn as_u32(&self) -> u32 {
+        match self {
+            Self::Disabled => 0,
+            Self::Strict => 1,
+            Self::Filter(_) => 2,
+        }
+    }
+}
+
+/// An installed seccomp filter, which is linked to the filters installed before it.
+pub struct SeccompFilter {
+    prog: BpfProg,
+    prev: Option<Arc<SeccompFilter>>,
+    /// The total length of this filter and the previous filters, as Linux counts it.
+    total_insns: usize,
+    /// Whether the actions taken by this filter are logged (i.e., `SECCOMP_FILTER_FLAG_LOG`).
+    log: bool,
+}
+
+impl fmt::Debug for SeccompFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SeccompFilter")
+            .field("total_insns", &self.total_insns)
+            .field("log", &self.log)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The maximum total length of the filters that are installed on a thread.
+const MAX_INSNS_PER_PATH: usize = 32768;
+
+impl SeccompFilter {
+    /// Checks and converts the classic BPF program, and creates a filter that is installed
+    /// after the previous filter.
+    pub fn new(filter: &[SockFilter], prev: Option<Arc<SeccompFilter>>, log: bool) -> Result<Self> {
+        let insns = cbpf::convert(filter, size_of::<SeccompData>() as u32)?;
+        // Like Linux, each filter is charged four more instructions as the overhead.
+        let total_insns = prev.as_ref().map_or(0, |prev| prev.total_insns) + filter.len() + 4;
+        if total_insns > MAX_INSNS_PER_PATH {
+            return_errno_with_message!(Errno::ENOMEM, "too many seccomp filters are installed");
+        }
+        let prog = BpfProg::new(BpfProgType::Seccomp, insns, |_| None)?;
+
+        Ok(Self {
+            prog,
+            prev,
+            total_insns,
+            log,
+        })
+    }
+
+    /// Runs this filter and the previous filters, and returns the most restrictive result.
+    ///
+    /// It also returns whether the filter that gives the result requests the action to be
+    /// logged.
+    fn run(&self, data: &SeccompData) -> (u32, bool) {
+        let mut result = SECCOMP_RET_ALLOW;
+        let mut log = false;
+        let mut filter = Some(self);
+        while let Some(current) = filter {
+            // A filter that fails to run kills the process, which should never happen since
+            // the filters can only load from their contexts in bounds.
+            let ret = current
+                .prog
+                .run(data.as_bytes())
+                .map_or(SECCOMP_RET_KILL_PROCESS, |ret| ret as u32);
+            if ((ret & SECCOMP_RET_ACTION_FULL) as i32)
+                < ((result & SECCOMP_RET_ACTION_FULL) as i32)
+            {
+                result = ret;
+                log = current.log;
+            }
+            filter = current.prev.as_deref();
+        }
+        (result, log)
+    }
+}
+
+/// The context of the seccomp filters, i.e., `struct seccomp_data`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct SeccompData {
+    nr: i32,
+    arch: u32,
+    instruction_pointer: u64,
+    args: [u64; 6],
+}
+
+/// Returns the architecture of the system calls, which is reported to the filters and in
+/// `SIGSYS`.
+pub fn audit_arch(is_compat: bool) -> u32 {
+    if is_compat {
+        AUDIT_ARCH_COMPAT
+    } else {
+        AUDIT_ARCH_CURRENT
+    }
+}
+
+#[cfg(target_arch = "riscv64")]
+const AUDIT_ARCH_CURRENT: u32 = 0xC000_00F3;
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH_CURRENT: u32 = 0xC000_003E;
+/// The architecture that is reported for a 32-bit program.
+#[cfg(target_arch = "riscv64")]
+const AUDIT_ARCH_COMPAT: u32 = 0x4000_00F3;
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH_COMPAT: u32 = 0x4000_0003;
+
+/// The system calls that are allowed in the strict mode, i.e., `read`, `write`, `exit` and
+/// `rt_sigreturn`.
+#[cfg(target_arch = "riscv64")]
+const STRICT_SYSCALLS: [u64; 4] = [63, 64, 93, 139];
+#[cfg(target_arch = "x86_64")]
+const STRICT_SYSCALLS: [u64; 4] = [0, 1, 60, 15];
+
+pub const SECCOMP_RET_KILL_PROCESS: u32 = 0x8000_0000;
+pub const SECCOMP_RET_KILL_THREAD: u32 = 0x0000_0000;
+pub const SECCOMP_RET_TRAP: u32 = 0x0003_0000;
+pub const SECCOMP_RET_ERRNO: u32 = 0x0005_0000;
+pub const SECCOMP_RET_USER_NOTIF: u32 = 0x7fc0_0000;
+pub const SECCOMP_RET_TRACE: u32 = 0x7ff0_0000;
+pub const SECCOMP_RET_LOG: u32 = 0x7ffc_0000;
+pub const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+
+const SECCOMP_RET_ACTION_FULL: u32 = 0xffff_0000;
+const SECCOMP_RET_DATA: u32 = 0x0000_ffff;
+
+/// The maximum errno that can be returned by `SECCOMP_RET_ERRNO`.
+const MAX_ERRNO: u32 = 4095;
+
+/// The known actions and their names, from the most restrictive to the least restrictive.
+const ACTIONS: [(u32, &str); 8] = [
+    (SECCOMP_RET_KILL_PROCESS, "kill_process"),
+    (SECCOMP_RET_KILL_THREAD, "kill_thread"),
+    (SECCOMP_RET_TRAP, "trap"),
+    (SECCOMP_RET_ERRNO, "errno"),
+    (SECCOMP_RET_USER_NOTIF, "user_notif"),
+    (SECCOMP_RET_TRACE, "trace"),
+    (SECCOMP_RET_LOG, "log"),
+    (SECCOMP_RET_ALLOW, "allow"),
+];
+
+/// The actions that can be audited, where each bit stands for an index of [`ACTIONS`].
+///
+/// All the actions other than `SECCOMP_RET_ALLOW` are audited by default.
+static ACTIONS_LOGGED: AtomicU32 = AtomicU32::new((1 << (ACTIONS.len() - 1)) - 1);
+
+/// Returns whether the action is known.
+pub fn is_action_available(action: u32) -> bool {
+    ACTIONS.iter().any(|(known, _)| *known == action)
+}
+
+/// Returns the names of the known actions, in `/proc/sys/kernel/seccomp/actions_avail`.
+pub fn available_actions() -> impl Iterator<Item = &'static str> {
+    ACTIONS.iter().map(|(_, name)| *name)
+}
+
+/// Returns the names of the actions that can be audited, in
+/// `/proc/sys/kernel/seccomp/actions_logged`.
+pub fn logged_actions() -> impl Iterator<Item = &'static str> {
+    let logged = ACTIONS_LOGGED.load(Ordering::Relaxed);
+    ACTIONS
+        .iter()
+        .enumerate()
+        .filter(move |(i, _)| logged & (1 << i) != 0)
+        .map(|(_, (_, name))| *name)
+}
+
+/// Sets the actions that can be audited from the names separated by whitespaces.
+///
+/// The names must be known, and `allow` cannot be audited.
+pub fn set_logged_actions(names: &str) -> Result<()> {
+    let mut logged = 0;
+    for name in names.split_whitespace() {
+        let Some(index) = ACTIONS.iter().position(|(_, known)| *known == name) else {
+            return_errno_with_message!(Errno::EINVAL, "unknown seccomp action");
+        };
+        if ACTIONS[index].0 == SECCOMP_RET_ALLOW {
+            return_errno_with_message!(Errno::EINVAL, "the allow action cannot be logged");
+        }
+        logged |= 1 << index;
+    }
+    ACTIONS_LOGGED.store(logged, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Returns whether the action should be audited, where `requested` tells whether the filter
+/// that takes the action is installed with `SECCOMP_FILTER_FLAG_LOG`.
+fn should_log(action: u32, requested: bool) -> bool {
+    let is_logged = |action: u32| {
+        let index = ACTIONS
+            .iter()
+            .position(|(known, _)| *known == action)
+            .unwrap();
+        ACTIONS_LOGGED.load(Ordering::Relaxed) & (1 << index) != 0
+    };
+
+    match action {
+        SECCOMP_RET_ALLOW => false,
+        SECCOMP_RET_TRAP | SECCOMP_RET_ERRNO | SECCOMP_RET_USER_NOTIF | SECCOMP_RET_TRACE => {
+            requested && is_logged(action)
+        }
+        SECCOMP_RET_LOG | SECCOMP_RET_KILL_THREAD => is_logged(action),
+        // The unknown actions are treated as `SECCOMP_RET_KILL_PROCESS`.
+        _ => is_logged(SECCOMP_RET_KILL_PROCESS),
+    }
+}
+
+/// Writes an audit record of the system call to the kernel log, in the format of the
+/// `AUDIT_SECCOMP` records of Linux.
+///
+/// `signal` is the signal that kills the thread or the process, if any. `code` is the value
+/// that is returned by the filters.
+fn audit(ctx: &Context, nr: u64, signal: Option<SigNum>, is_compat: bool, ip: usize, code: u32) {
+    const AUDIT_SECCOMP: u32 = 1326;
+
+    let credentials = ctx.posix_thread.credentials();
+    let comm = ctx
+        .posix_thread
+        .thread_name()
+        .lock()
+        .as_ref()
+        .and_then(|name| name.name().ok().flatten())
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    info!(
+        "audit: type={} uid={} gid={} pid={} comm=\"{}\" exe=\"{}\" sig={} arch={:x} syscall={} compat={} ip={:#x} code={:#x}",
+        AUDIT_SECCOMP,
+        u32::from(credentials.ruid()),
+        u32::from(credentials.rgid()),
+        ctx.process.pid(),
+        comm,
+        ctx.process.executable_path(),
+        signal.map_or(0, |signal| signal.as_u8()),
+        audit_arch(is_compat),
+        nr,
+        is_compat as u8,
+        ip,
+        code
+    );
+}
+
+/// Checks whether the current thread is allowed to make the system call.
+///
+/// It returns `None` if the system call is allowed. Otherwise, the system call should not be
+/// made and the returned value is set as its return value. The current thread or process may
+/// have been exited in the latter case.
+///
+/// The system call is made by a 32-bit program if `is_compat` is true, in which case the
+/// filters see the architecture of the 32-bit program.
+pub fn check_syscall(
+    ctx: &Context,
+    nr: u64,
+    args: &[u64; 6],
+    ip: usize,
+    is_compat: bool,
+) -> Option<isize> {
+    let filter = match ctx.posix_thread.credentials().seccomp_mode() {
+        SeccompMode::Disabled => return None,
+        SeccompMode::Strict => {
+            if STRICT_SYSCALLS.contains(&nr) {
+                return None;
+            }
+            if should_log(SECCOMP_RET_KILL_THREAD, true) {
+                audit(
+                    ctx,
+                    nr,
+                    Some(SIGKILL),
+                    is_compat,
+                    ip,
+                    SECCOMP_RET_KILL_THREAD,
+                );
+            }
+            do_exit_group(TermStatus::Killed(SIGKILL));
+            return Some(-(Errno::ENOSYS as isize));
+        }
+        SeccompMode::Filter(filter) => filter,
+    };
+
+    let data = SeccompData {
+        nr: nr as i32,
+        arch: audit_arch(is_compat),
+        instruction_pointer: ip as u64,
+        args: *args,
+    };
+    let (ret, log) = filter.run(&data);
+    let action = ret & SECCOMP_RET_ACTION_FULL;
+    let ret_data = ret & SECCOMP_RET_DATA;
+
+    if should_log(action, log) {
+        let signal = match action {
+            SECCOMP_RET_ALLOW
+            | SECCOMP_RET_LOG
+            | SECCOMP_RET_ERRNO
+            | SECCOMP_RET_TRAP
+            | SECCOMP_RET_TRACE
+            | SECCOMP_RET_USER_NOTIF => None,
+            _ => Some(SIGSYS),
+        };
+        audit(ctx, nr, signal, is_compat, ip, ret);
+    }
+
+    match action {
+        SECCOMP_RET_ALLOW | SECCOMP_RET_LOG => None,
+        SECCOMP_RET_ERRNO => Some(-(ret_data.min(MAX_ERRNO) as isize)),
+        SECCOMP_RET_TRAP => {
+            let signal = SyscallSignal::new(
+                SYS_SECCOMP,
+                ret_data as i32,
+                ip,
+                nr as i32,
+                audit_arch(is_compat),
+            );
+            ctx.posix_thread.enqueue_signal(Box::new(signal));
+            Some(-(Errno::ENOSYS as isize))
+        }
+        SECCOMP_RET_TRACE | SECCOMP_RET_USER_NOTIF => Some(-(Errno::ENOSYS as isize)),
+        SECCOMP_RET_KILL_THREAD => {
+            do_exit(TermStatus::Killed(SIGSYS));
+            Some(-(Errno::ENOSYS as isize))
+        }
+        // The unknown actions are treated as `SECCOMP_RET_KILL_PROCESS`.
+        _ => {
+            do_exit_group(TermStatus::Killed(SIGSYS));
+            Some(-(Errno::ENOSYS as isize))
+        }
+    }
+}
@@ -0,0 +1,115 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::SyscallReturn;
+use crate::{
+    prelude::*,
+    security::keys::{
+        lookup_key, perm_mask, Key, KeyLookupFlags, KeyPerm, KeySerial, KeyType,
+        MAX_DESCRIPTION_LEN,
+    },
+};
+
+/// The maximum length of the payloads passed from the user space.
+const MAX_USER_PAYLOAD_LEN: usize = 1024 * 1024 - 1;
+
+/// The maximum length of key type names, including the final `\0` byte.
+const KEY_TYPE_NAME_MAX: usize = 32;
+
+pub fn sys_add_key(
+    type_addr: Vaddr,
+    description_addr: Vaddr,
+    payload_addr: Vaddr,
+    payload_len: usize,
+    keyring_serial: KeySerial,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let user_space = ctx.user_space();
+    let key_type = read_key_type(&user_space, type_addr)?;
+    let description = read_description(&user_space, description_addr)?;
+    let payload = read_payload(&user_space, payload_addr, payload_len)?;
+    debug!(
+        "type = {:?}, description = {:?}, payload_len = {}, keyring = {}",
+        key_type, description, payload_len, keyring_serial
+    );
+
+    if key_type == KeyType::Keyring && description.starts_with('.') {
+        return_errno_with_message!(Errno::EPERM, "the keyring name is reserved");
+    }
+    key_type.check(&description, &payload)?;
+
+    let credentials = ctx.posix_thread.credentials();
+    let keyring = lookup_key(ctx.posix_thread, keyring_serial, KeyLookupFlags::CREATE)?;
+    keyring.check_permission(&credentials, KeyPerm::WRITE)?;
+
+    // Update the key of the same type and description in the keyring, if any. Keyrings cannot
+    // be updated, so a new keyring replaces the old one instead.
+    if key_type != KeyType::Keyring {
+        let existing = keyring.key().links()?.into_iter().find(|key| {
+            key.key_type() == key_type
+                && key.description() == description
+                && key.check_valid().is_ok()
+        });
+        if let Some(existing) = existing {
+            existing.check_permission(&credentials, keyring.is_possessed(), KeyPerm::WRITE)?;
+            existing.update(payload)?;
+            return Ok(SyscallReturn::Return(existing.serial() as _));
+        }
+    }
+
+    let key = Key::new(
+        key_type,
+        description,
+        payload,
+        credentials.fsuid(),
+        credentials.fsgid(),
+        default_perm(key_type),
+    );
+    keyring.key().link(key.clone())?;
+
+    Ok(SyscallReturn::Return(key.serial() as _))
+}
+
+/// Returns the permissions of a new key, which are granted to the possessors, and the owner
+/// can only view the key.
+fn default_perm(key_type: KeyType) -> u32 {
+    let possessor_perm = match key_type {
+        KeyType::User | KeyType::Keyring => KeyPerm::ALL,
+        KeyType::Logon => KeyPerm::ALL - KeyPerm::READ,
+    };
+    perm_mask(
+        possessor_perm,
+        KeyPerm::VIEW,
+        KeyPerm::empty(),
+        KeyPerm::empty(),
+    )
+}
+
+pub(super) fn read_key_type(user_space: &CurrentUserSpace, type_addr: Vaddr) -> Result<KeyType> {
+    let name = user_space.read_cstring(type_addr, KEY_TYPE_NAME_MAX)?;
+    KeyType::from_name(name.to_str()?)
+}
+
+pub(super) fn read_description(
+    user_space: &CurrentUserSpace,
+    description_addr: Vaddr,
+) -> Result<String> {
+    let description = user_space.read_cstring(description_addr, MAX_DESCRIPTION_LEN + 1)?;
+    Ok(description.to_str()?.to_string())
+}
+
+pub(super) fn read_payload(
+    user_space: &CurrentUserSpace,
+    payload_addr: Vaddr,
+    payload_len: usize,
+) -> Result<Vec<u8>> {
+    if payload_len > MAX_USER_PAYLOAD_LEN {
+        return_errno_with_message!(Errno::EINVAL, "the payload is too long");
+    }
+    if payload_len == 0 {
+        return Ok(Vec::new());
+    }
+
+    let mut payload = vec![0u8; payload_len];
+    user_space.read_bytes(payload_addr, &mut VmWriter::from(payload.as_mut_slice()))?;
+    Ok(payload)
+}
@@ -0,0 +1,56 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{clock_gettime::ClockId, SyscallReturn};
+use crate::{
+    prelude::*,
+    process::credentials::capabilities::CapSet,
+    time::{
+        clockid_t,
+        timekeeping::{self, timex_t, ADJ_OFFSET_SS_READ},
+    },
+};
+
+pub fn sys_adjtimex(timex_addr: Vaddr, ctx: &Context) -> Result<SyscallReturn> {
+    debug!("timex_addr = {:#x}", timex_addr);
+
+    do_adjtimex(timex_addr, ctx)
+}
+
+pub fn sys_clock_adjtime(
+    clockid: clockid_t,
+    timex_addr: Vaddr,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    debug!("clockid = {}, timex_addr = {:#x}", clockid, timex_addr);
+
+    let clock_id = ClockId::try_from(clockid)?;
+    if clock_id != ClockId::CLOCK_REALTIME {
+        return_errno_with_message!(
+            Errno::EOPNOTSUPP,
+            "only the real-time clock can be adjusted"
+        );
+    }
+
+    do_adjtimex(timex_addr, ctx)
+}
+
+fn do_adjtimex(timex_addr: Vaddr, ctx: &Context) -> Result<SyscallReturn> {
+    let user_space = ctx.user_space();
+    let mut timex = user_space.read_val::<timex_t>(timex_addr)?;
+
+    if timex.modes != 0
+        && timex.modes != ADJ_OFFSET_SS_READ
+        && !ctx
+            .posix_thread
+            .credentials()
+            .effective_capset()
+            .contains(CapSet::SYS_TIME)
+    {
+        return_errno_with_message!(Errno::EPERM, "`CAP_SYS_TIME` is required");
+    }
+
+    let state = timekeeping::adjtimex(&mut timex)?;
+    user_space.write_val(timex_addr, &timex)?;
+
+    Ok(SyscallReturn::Return(state as _))
+}
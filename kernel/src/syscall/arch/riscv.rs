@@ -1,9 +1,14 @@
 // SPDX-License-Identifier: MPL-2.0
 
+pub mod compat;
+
 use crate::syscall::{
     accept::{sys_accept, sys_accept4},
     access::sys_faccessat,
+    add_key::sys_add_key,
+    adjtimex::{sys_adjtimex, sys_clock_adjtime},
     bind::sys_bind,
+    bpf::sys_bpf,
     brk::sys_brk,
     capget::sys_capget,
     capset::sys_capset,
@@ -15,6 +20,7 @@ use crate::syscall::{
     clone::{sys_clone, sys_clone3},
     close::sys_close,
     connect::sys_connect,
+    copy_file_range::sys_copy_file_range,
     dup::{sys_dup, sys_dup3},
     epoll::{sys_epoll_create1, sys_epoll_ctl, sys_epoll_pwait},
     eventfd::sys_eventfd2,
@@ -24,6 +30,7 @@ use crate::syscall::{
     fallocate::sys_fallocate,
     fcntl::sys_fcntl,
     flock::sys_flock,
+    fsopen::{sys_fsconfig, sys_fsopen},
     fsync::{sys_fdatasync, sys_fsync},
     futex::sys_futex,
     get_priority::sys_get_priority,
@@ -50,33 +57,56 @@ use crate::syscall::{
     getuid::sys_getuid,
     impl_syscall_nums_and_dispatch_fn,
     ioctl::sys_ioctl,
+    kcmp::sys_kcmp,
+    kexec_file_load::sys_kexec_file_load,
+    kexec_load::sys_kexec_load,
+    keyctl::sys_keyctl,
     kill::sys_kill,
+    landlock::{sys_landlock_add_rule, sys_landlock_create_ruleset, sys_landlock_restrict_self},
     link::sys_linkat,
     listen::sys_listen,
     lseek::sys_lseek,
     madvise::sys_madvise,
+    mincore::sys_mincore,
     mkdir::sys_mkdirat,
     mknod::sys_mknodat,
+    mlock::{sys_mlock, sys_mlock2, sys_mlockall, sys_munlock, sys_munlockall},
     mmap::sys_mmap,
-    mount::sys_mount,
+    mount::{sys_fsmount, sys_mount, sys_move_mount},
     mprotect::sys_mprotect,
+    mq_getsetattr::sys_mq_getsetattr,
+    mq_notify::sys_mq_notify,
+    mq_open::sys_mq_open,
+    mq_timedreceive::sys_mq_timedreceive,
+    mq_timedsend::sys_mq_timedsend,
+    mq_unlink::sys_mq_unlink,
+    msgctl::sys_msgctl,
+    msgget::sys_msgget,
+    msgrcv::sys_msgrcv,
+    msgsnd::sys_msgsnd,
     msync::sys_msync,
     munmap::sys_munmap,
     nanosleep::{sys_clock_nanosleep, sys_nanosleep},
-    open::sys_openat,
+    open::{sys_openat, sys_openat2},
+    perf_event_open::sys_perf_event_open,
     pipe::sys_pipe2,
     prctl::sys_prctl,
     pread64::sys_pread64,
     preadv::{sys_preadv, sys_preadv2, sys_readv},
     prlimit64::{sys_getrlimit, sys_prlimit64, sys_setrlimit},
+    process_vm::{sys_process_vm_readv, sys_process_vm_writev},
     pselect6::sys_pselect6,
     pwrite64::sys_pwrite64,
     pwritev::{sys_pwritev, sys_pwritev2, sys_writev},
     read::sys_read,
     readlink::sys_readlinkat,
+    reboot::sys_reboot,
     recvfrom::sys_recvfrom,
     recvmsg::sys_recvmsg,
-    rename::sys_renameat,
+    rename::{sys_renameat, sys_renameat2},
+    request_key::sys_request_key,
+    riscv_hwprobe::sys_riscv_hwprobe,
+    rseq::sys_rseq,
     rt_sigaction::sys_rt_sigaction,
     rt_sigpending::sys_rt_sigpending,
     rt_sigprocmask::sys_rt_sigprocmask,
@@ -91,6 +121,7 @@ use crate::syscall::{
     sched_setparam::sys_sched_setparam,
     sched_setscheduler::sys_sched_setscheduler,
     sched_yield::sys_sched_yield,
+    seccomp::sys_seccomp,
     semctl::sys_semctl,
     semget::sys_semget,
     semop::{sys_semop, sys_semtimedop},
@@ -113,24 +144,32 @@ use crate::syscall::{
     setsid::sys_setsid,
     setsockopt::sys_setsockopt,
     setuid::sys_setuid,
+    shmat::sys_shmat,
+    shmctl::sys_shmctl,
+    shmdt::sys_shmdt,
+    shmget::sys_shmget,
     shutdown::sys_shutdown,
     sigaltstack::sys_sigaltstack,
     signalfd::sys_signalfd4,
     socket::sys_socket,
     socketpair::sys_socketpair,
-    stat::{sys_fstat, sys_fstatat},
+    splice::sys_splice,
+    stat::{sys_fstat, sys_fstatat, sys_statx},
     statfs::{sys_fstatfs, sys_statfs},
     symlink::sys_symlinkat,
     sync::sys_sync,
+    syslog::sys_syslog,
     tgkill::sys_tgkill,
-    timer_create::{sys_timer_create, sys_timer_delete},
+    timer_create::{sys_timer_create, sys_timer_delete, sys_timer_getoverrun},
     timer_settime::{sys_timer_gettime, sys_timer_settime},
+    times::sys_times,
     truncate::{sys_ftruncate, sys_truncate},
     umask::sys_umask,
     umount::sys_umount,
     uname::sys_uname,
     unlink::sys_unlinkat,
     utimens::sys_utimensat,
+    vmsplice::sys_vmsplice,
     wait4::sys_wait4,
     waitid::sys_waitid,
     write::sys_write,
@@ -184,6 +223,8 @@ impl_syscall_nums_and_dispatch_fn! {
     SYS_SENDFILE64 = 71          => sys_sendfile(args[..4]);
     SYS_PSELECT6 = 72            => sys_pselect6(args[..6]);
     SYS_SIGNALFD4 = 74           => sys_signalfd4(args[..4]);
+    SYS_VMSPLICE = 75            => sys_vmsplice(args[..4]);
+    SYS_SPLICE = 76              => sys_splice(args[..6]);
     SYS_READLINKAT = 78          => sys_readlinkat(args[..4]);
     SYS_NEWFSTATAT = 79          => sys_fstatat(args[..4]);
     SYS_NEWFSTAT = 80            => sys_fstat(args[..2]);
@@ -201,8 +242,13 @@ impl_syscall_nums_and_dispatch_fn! {
     SYS_NANOSLEEP = 101          => sys_nanosleep(args[..2]);
     SYS_GETITIMER = 102          => sys_getitimer(args[..2]);
     SYS_SETITIMER = 103          => sys_setitimer(args[..3]);
+    SYS_KEXEC_LOAD = 104         => sys_kexec_load(args[..4]);
     SYS_TIMER_CREATE = 107       => sys_timer_create(args[..3]);
+    SYS_TIMER_GETTIME = 108      => sys_timer_gettime(args[..2]);
+    SYS_TIMER_GETOVERRUN = 109   => sys_timer_getoverrun(args[..1]);
+    SYS_TIMER_SETTIME = 110      => sys_timer_settime(args[..4]);
     SYS_TIMER_DELETE = 111       => sys_timer_delete(args[..1]);
+    SYS_SYSLOG = 116             => sys_syslog(args[..3]);
     SYS_SCHED_SETPARAM = 118     => sys_sched_setparam(args[..2]);
     SYS_SCHED_SETSCHEDULER = 119 => sys_sched_setscheduler(args[..3]);
     SYS_SCHED_GETSCHEDULER = 120 => sys_sched_getscheduler(args[..1]);
@@ -221,6 +267,7 @@ impl_syscall_nums_and_dispatch_fn! {
     SYS_RT_SIGPENDING = 136      => sys_rt_sigpending(args[..2]);
     SYS_SET_PRIORITY = 140       => sys_set_priority(args[..3]);
     SYS_GET_PRIORITY = 141       => sys_get_priority(args[..2]);
+    SYS_REBOOT = 142             => sys_reboot(args[..4]);
     SYS_SETREGID = 143           => sys_setregid(args[..2]);
     SYS_SETGID = 144             => sys_setgid(args[..1]);
     SYS_SETREUID = 145           => sys_setreuid(args[..2]);
@@ -231,6 +278,7 @@ impl_syscall_nums_and_dispatch_fn! {
     SYS_GETRESGID = 150          => sys_getresgid(args[..3]);
     SYS_SETFSUID = 151           => sys_setfsuid(args[..1]);
     SYS_SETFSGID = 152           => sys_setfsgid(args[..1]);
+    SYS_TIMES = 153              => sys_times(args[..1]);
     SYS_SETPGID = 154            => sys_setpgid(args[..2]);
     SYS_GETPGID = 155            => sys_getpgid(args[..1]);
     SYS_GETSID = 156             => sys_getsid(args[..1]);
@@ -245,6 +293,7 @@ impl_syscall_nums_and_dispatch_fn! {
     SYS_PRCTL = 167              => sys_prctl(args[..5]);
     SYS_GETCPU = 168             => sys_getcpu(args[..3]);
     SYS_GETTIMEOFDAY = 169       => sys_gettimeofday(args[..1]);
+    SYS_ADJTIMEX = 171           => sys_adjtimex(args[..1]);
     SYS_GETPID = 172             => sys_getpid(args[..0]);
     SYS_GETPPID = 173            => sys_getppid(args[..0]);
     SYS_GETUID = 174             => sys_getuid(args[..0]);
@@ -252,9 +301,24 @@ impl_syscall_nums_and_dispatch_fn! {
     SYS_GETGID = 176             => sys_getgid(args[..0]);
     SYS_GETEGID = 177            => sys_getegid(args[..0]);
     SYS_GETTID = 178             => sys_gettid(args[..0]);
+    SYS_MQ_OPEN = 180            => sys_mq_open(args[..4]);
+    SYS_MQ_UNLINK = 181          => sys_mq_unlink(args[..1]);
+    SYS_MQ_TIMEDSEND = 182       => sys_mq_timedsend(args[..5]);
+    SYS_MQ_TIMEDRECEIVE = 183    => sys_mq_timedreceive(args[..5]);
+    SYS_MQ_NOTIFY = 184          => sys_mq_notify(args[..2]);
+    SYS_MQ_GETSETATTR = 185      => sys_mq_getsetattr(args[..3]);
+    SYS_MSGGET = 186             => sys_msgget(args[..2]);
+    SYS_MSGCTL = 187             => sys_msgctl(args[..3]);
+    SYS_MSGRCV = 188             => sys_msgrcv(args[..5]);
+    SYS_MSGSND = 189             => sys_msgsnd(args[..4]);
     SYS_SEMGET = 190             => sys_semget(args[..3]);
     SYS_SEMCTL = 191             => sys_semctl(args[..4]);
+    SYS_SEMTIMEDOP = 192         => sys_semtimedop(args[..4]);
     SYS_SEMOP = 193              => sys_semop(args[..3]);
+    SYS_SHMGET = 194             => sys_shmget(args[..3]);
+    SYS_SHMCTL = 195             => sys_shmctl(args[..3]);
+    SYS_SHMAT = 196              => sys_shmat(args[..3]);
+    SYS_SHMDT = 197              => sys_shmdt(args[..1]);
     SYS_SOCKET = 198             => sys_socket(args[..3]);
     SYS_SOCKETPAIR = 199         => sys_socketpair(args[..4]);
     SYS_BIND = 200               => sys_bind(args[..3]);
@@ -272,27 +336,55 @@ impl_syscall_nums_and_dispatch_fn! {
     SYS_RECVMSG = 212            => sys_recvmsg(args[..3]);
     SYS_BRK = 214                => sys_brk(args[..1]);
     SYS_MUNMAP = 215             => sys_munmap(args[..2]);
+    SYS_ADD_KEY = 217            => sys_add_key(args[..5]);
+    SYS_REQUEST_KEY = 218        => sys_request_key(args[..4]);
+    SYS_KEYCTL = 219             => sys_keyctl(args[..5]);
     SYS_CLONE = 220              => sys_clone(args[..5], &user_ctx);
     SYS_EXECVE = 221             => sys_execve(args[..3], &mut user_ctx);
     SYS_MMAP = 222               => sys_mmap(args[..6]);
     SYS_MPROTECT = 226           => sys_mprotect(args[..3]);
     SYS_MSYNC = 227              => sys_msync(args[..3]);
+    SYS_MLOCK = 228              => sys_mlock(args[..2]);
+    SYS_MUNLOCK = 229            => sys_munlock(args[..2]);
+    SYS_MLOCKALL = 230           => sys_mlockall(args[..1]);
+    SYS_MUNLOCKALL = 231         => sys_munlockall(args[..0]);
+    SYS_MINCORE = 232            => sys_mincore(args[..3]);
     SYS_MADVISE = 233            => sys_madvise(args[..3]);
+    SYS_PERF_EVENT_OPEN = 241    => sys_perf_event_open(args[..5]);
     SYS_ACCEPT4 = 242            => sys_accept4(args[..4]);
+    SYS_RISCV_HWPROBE = 258      => sys_riscv_hwprobe(args[..5]);
     SYS_WAIT4 = 260              => sys_wait4(args[..4]);
-    // SYS_PRLIMIT64 = 261          => sys_prlimit64(args[..4]);
+    SYS_PRLIMIT64 = 261          => sys_prlimit64(args[..4]);
+    SYS_CLOCK_ADJTIME = 266      => sys_clock_adjtime(args[..2]);
+    SYS_PROCESS_VM_READV = 270   => sys_process_vm_readv(args[..6]);
+    SYS_PROCESS_VM_WRITEV = 271  => sys_process_vm_writev(args[..6]);
+    SYS_KCMP = 272               => sys_kcmp(args[..5]);
     SYS_SCHED_SETATTR = 274      => sys_sched_setattr(args[..3]);
     SYS_SCHED_GETATTR = 275      => sys_sched_getattr(args[..4]);
+    SYS_RENAMEAT2 = 276          => sys_renameat2(args[..5]);
+    SYS_SECCOMP = 277            => sys_seccomp(args[..3]);
     SYS_GETRANDOM = 278          => sys_getrandom(args[..3]);
+    SYS_BPF = 280                => sys_bpf(args[..3]);
     SYS_EXECVEAT = 281           => sys_execveat(args[..5], &mut user_ctx);
+    SYS_MLOCK2 = 284             => sys_mlock2(args[..3]);
+    SYS_COPY_FILE_RANGE = 285    => sys_copy_file_range(args[..6]);
     SYS_PREADV2 = 286            => sys_preadv2(args[..5]);
     SYS_PWRITEV2 = 287           => sys_pwritev2(args[..5]);
-    SYS_PRLIMIT64 = 302          => sys_prlimit64(args[..4]);
+    SYS_STATX = 291              => sys_statx(args[..5]);
+    SYS_RSEQ = 293               => sys_rseq(args[..4]);
+    SYS_KEXEC_FILE_LOAD = 294    => sys_kexec_file_load(args[..5]);
     SYS_CLOCK_GETTIME = 403      => sys_clock_gettime(args[..2]);
     SYS_CLOCK_NANOSLEEP = 407    => sys_clock_nanosleep(args[..4]);
-    SYS_TIMER_GETTIME = 408      => sys_timer_gettime(args[..2]);
-    SYS_TIMER_SETTIME = 409      => sys_timer_settime(args[..4]);
+    SYS_TIMER_GETTIME64 = 408    => sys_timer_gettime(args[..2]);
+    SYS_TIMER_SETTIME64 = 409    => sys_timer_settime(args[..4]);
     SYS_UTIMENSAT = 412          => sys_utimensat(args[..4]);
-    SYS_SEMTIMEDOP = 420         => sys_semtimedop(args[..4]);
+    SYS_MOVE_MOUNT = 429         => sys_move_mount(args[..5]);
+    SYS_FSOPEN = 430             => sys_fsopen(args[..2]);
+    SYS_FSCONFIG = 431           => sys_fsconfig(args[..5]);
+    SYS_FSMOUNT = 432            => sys_fsmount(args[..3]);
     SYS_CLONE3 = 435             => sys_clone3(args[..2], &user_ctx);
+    SYS_OPENAT2 = 437            => sys_openat2(args[..4]);
+    SYS_LANDLOCK_CREATE_RULESET = 444 => sys_landlock_create_ruleset(args[..3]);
+    SYS_LANDLOCK_ADD_RULE = 445  => sys_landlock_add_rule(args[..4]);
+    SYS_LANDLOCK_RESTRICT_SELF = 446 => sys_landlock_restrict_self(args[..2]);
 }
@@ -0,0 +1,151 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The system calls of 32-bit RISC-V programs (i.e., the programs of `rv32`/`ilp32`), which run
+//! on the 64-bit kernel if the CPU supports 32-bit user mode (i.e., `sstatus.UXL` can be set to
+//! 32).
+//!
+//! The system call numbers are those of the generic 32-bit architectures. The arguments have
+//! been zero-extended from 32 bits (see [`LinuxAbi`]), so a system call is passed through to the
+//! native handler if its arguments and user space structs are of the same layout in 32-bit
+//! programs. Otherwise, a `compat_sys_*` handler translates the 64-bit values that are split
+//! into two registers and the structs that contain `long`s or pointers (e.g., `stat64`,
+//! `timespec` and `iovec`).
+//!
+//! Compared to Linux, the signal handlers, the vDSO and the system calls that are not listed
+//! here are not supported in 32-bit programs.
+//!
+//! [`LinuxAbi`]: crate::cpu::LinuxAbi
+
+use crate::syscall::{
+    access::sys_faccessat,
+    brk::sys_brk,
+    chdir::{sys_chdir, sys_fchdir},
+    chmod::{sys_fchmod, sys_fchmodat},
+    chown::{sys_fchown, sys_fchownat},
+    clock_gettime::{compat_sys_clock_gettime, sys_clock_gettime},
+    clone::{sys_clone, sys_clone3},
+    close::sys_close,
+    dup::{sys_dup, sys_dup3},
+    execve::sys_execve,
+    exit::sys_exit,
+    exit_group::sys_exit_group,
+    fcntl::sys_fcntl,
+    futex::sys_futex,
+    getcwd::sys_getcwd,
+    getdents64::sys_getdents64,
+    getegid::sys_getegid,
+    geteuid::sys_geteuid,
+    getgid::sys_getgid,
+    getpid::sys_getpid,
+    getppid::sys_getppid,
+    getrandom::sys_getrandom,
+    gettid::sys_gettid,
+    gettimeofday::compat_sys_gettimeofday,
+    getuid::sys_getuid,
+    impl_syscall_nums_and_dispatch_fn,
+    ioctl::sys_ioctl,
+    kill::sys_kill,
+    link::sys_linkat,
+    lseek::compat_sys_llseek,
+    madvise::sys_madvise,
+    mkdir::sys_mkdirat,
+    mmap::compat_sys_mmap2,
+    mprotect::sys_mprotect,
+    munmap::sys_munmap,
+    nanosleep::{compat_sys_clock_nanosleep, compat_sys_nanosleep, sys_clock_nanosleep},
+    open::sys_openat,
+    pipe::sys_pipe2,
+    pread64::compat_sys_pread64,
+    preadv::{compat_sys_preadv, compat_sys_readv},
+    prlimit64::sys_prlimit64,
+    pwrite64::compat_sys_pwrite64,
+    pwritev::{compat_sys_pwritev, compat_sys_writev},
+    read::sys_read,
+    readlink::sys_readlinkat,
+    rename::sys_renameat2,
+    rseq::sys_rseq,
+    rt_sigprocmask::sys_rt_sigprocmask,
+    sched_yield::sys_sched_yield,
+    set_tid_address::sys_set_tid_address,
+    stat::{compat_sys_fstat64, compat_sys_fstatat64, sys_statx},
+    symlink::sys_symlinkat,
+    tgkill::sys_tgkill,
+    truncate::{compat_sys_ftruncate64, compat_sys_truncate64},
+    uname::sys_uname,
+    unlink::sys_unlinkat,
+    utimens::sys_utimensat,
+    write::sys_write,
+};
+
+impl_syscall_nums_and_dispatch_fn! {
+    SYS_GETCWD = 17              => sys_getcwd(args[..2]);
+    SYS_DUP = 23                 => sys_dup(args[..1]);
+    SYS_DUP3 = 24                => sys_dup3(args[..3]);
+    SYS_FCNTL64 = 25             => sys_fcntl(args[..3]);
+    SYS_IOCTL = 29               => sys_ioctl(args[..3]);
+    SYS_MKDIRAT = 34             => sys_mkdirat(args[..3]);
+    SYS_UNLINKAT = 35            => sys_unlinkat(args[..3]);
+    SYS_SYMLINKAT = 36           => sys_symlinkat(args[..3]);
+    SYS_LINKAT = 37              => sys_linkat(args[..5]);
+    SYS_TRUNCATE64 = 45          => compat_sys_truncate64(args[..3]);
+    SYS_FTRUNCATE64 = 46         => compat_sys_ftruncate64(args[..3]);
+    SYS_FACCESSAT = 48           => sys_faccessat(args[..3]);
+    SYS_CHDIR = 49               => sys_chdir(args[..1]);
+    SYS_FCHDIR = 50              => sys_fchdir(args[..1]);
+    SYS_FCHMOD = 52              => sys_fchmod(args[..2]);
+    SYS_FCHMODAT = 53            => sys_fchmodat(args[..3]);
+    SYS_FCHOWNAT = 54            => sys_fchownat(args[..5]);
+    SYS_FCHOWN = 55              => sys_fchown(args[..3]);
+    SYS_OPENAT = 56              => sys_openat(args[..4]);
+    SYS_CLOSE = 57               => sys_close(args[..1]);
+    SYS_PIPE2 = 59               => sys_pipe2(args[..2]);
+    SYS_GETDENTS64 = 61          => sys_getdents64(args[..3]);
+    SYS_LLSEEK = 62              => compat_sys_llseek(args[..5]);
+    SYS_READ = 63                => sys_read(args[..3]);
+    SYS_WRITE = 64               => sys_write(args[..3]);
+    SYS_READV = 65               => compat_sys_readv(args[..3]);
+    SYS_WRITEV = 66              => compat_sys_writev(args[..3]);
+    SYS_PREAD64 = 67             => compat_sys_pread64(args[..5]);
+    SYS_PWRITE64 = 68            => compat_sys_pwrite64(args[..5]);
+    SYS_PREADV = 69              => compat_sys_preadv(args[..5]);
+    SYS_PWRITEV = 70             => compat_sys_pwritev(args[..5]);
+    SYS_READLINKAT = 78          => sys_readlinkat(args[..4]);
+    SYS_FSTATAT64 = 79           => compat_sys_fstatat64(args[..4]);
+    SYS_FSTAT64 = 80             => compat_sys_fstat64(args[..2]);
+    SYS_EXIT = 93                => sys_exit(args[..1]);
+    SYS_EXIT_GROUP = 94          => sys_exit_group(args[..1]);
+    SYS_SET_TID_ADDRESS = 96     => sys_set_tid_address(args[..1]);
+    SYS_NANOSLEEP = 101          => compat_sys_nanosleep(args[..2]);
+    SYS_CLOCK_GETTIME = 113      => compat_sys_clock_gettime(args[..2]);
+    SYS_CLOCK_NANOSLEEP = 115    => compat_sys_clock_nanosleep(args[..4]);
+    SYS_SCHED_YIELD = 124        => sys_sched_yield(args[..0]);
+    SYS_KILL = 129               => sys_kill(args[..2]);
+    SYS_TGKILL = 131             => sys_tgkill(args[..3]);
+    SYS_RT_SIGPROCMASK = 135     => sys_rt_sigprocmask(args[..4]);
+    SYS_UNAME = 160              => sys_uname(args[..1]);
+    SYS_GETTIMEOFDAY = 169       => compat_sys_gettimeofday(args[..1]);
+    SYS_GETPID = 172             => sys_getpid(args[..0]);
+    SYS_GETPPID = 173            => sys_getppid(args[..0]);
+    SYS_GETUID = 174             => sys_getuid(args[..0]);
+    SYS_GETEUID = 175            => sys_geteuid(args[..0]);
+    SYS_GETGID = 176             => sys_getgid(args[..0]);
+    SYS_GETEGID = 177            => sys_getegid(args[..0]);
+    SYS_GETTID = 178             => sys_gettid(args[..0]);
+    SYS_BRK = 214                => sys_brk(args[..1]);
+    SYS_MUNMAP = 215             => sys_munmap(args[..2]);
+    SYS_CLONE = 220              => sys_clone(args[..5], &user_ctx);
+    SYS_EXECVE = 221             => sys_execve(args[..3], &mut user_ctx);
+    SYS_MMAP2 = 222              => compat_sys_mmap2(args[..6]);
+    SYS_MPROTECT = 226           => sys_mprotect(args[..3]);
+    SYS_MADVISE = 233            => sys_madvise(args[..3]);
+    SYS_PRLIMIT64 = 261          => sys_prlimit64(args[..4]);
+    SYS_RENAMEAT2 = 276          => sys_renameat2(args[..5]);
+    SYS_GETRANDOM = 278          => sys_getrandom(args[..3]);
+    SYS_STATX = 291              => sys_statx(args[..5]);
+    SYS_RSEQ = 293               => sys_rseq(args[..4]);
+    SYS_CLOCK_GETTIME64 = 403    => sys_clock_gettime(args[..2]);
+    SYS_CLOCK_NANOSLEEP_TIME64 = 407 => sys_clock_nanosleep(args[..4]);
+    SYS_UTIMENSAT_TIME64 = 412   => sys_utimensat(args[..4]);
+    SYS_FUTEX_TIME64 = 422       => sys_futex(args[..6]);
+    SYS_CLONE3 = 435             => sys_clone3(args[..2], &user_ctx);
+}
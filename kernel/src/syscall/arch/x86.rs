@@ -3,9 +3,12 @@
 use crate::syscall::{
     accept::{sys_accept, sys_accept4},
     access::{sys_access, sys_faccessat},
+    add_key::sys_add_key,
+    adjtimex::{sys_adjtimex, sys_clock_adjtime},
     alarm::sys_alarm,
     arch_prctl::sys_arch_prctl,
     bind::sys_bind,
+    bpf::sys_bpf,
     brk::sys_brk,
     capget::sys_capget,
     capset::sys_capset,
@@ -17,6 +20,7 @@ use crate::syscall::{
     clone::{sys_clone, sys_clone3},
     close::sys_close,
     connect::sys_connect,
+    copy_file_range::sys_copy_file_range,
     dup::{sys_dup, sys_dup2, sys_dup3},
     epoll::{sys_epoll_create, sys_epoll_create1, sys_epoll_ctl, sys_epoll_pwait, sys_epoll_wait},
     eventfd::{sys_eventfd, sys_eventfd2},
@@ -27,6 +31,7 @@ use crate::syscall::{
     fcntl::sys_fcntl,
     flock::sys_flock,
     fork::{sys_fork, sys_vfork},
+    fsopen::{sys_fsconfig, sys_fsopen},
     fsync::{sys_fdatasync, sys_fsync},
     futex::sys_futex,
     get_priority::sys_get_priority,
@@ -55,38 +60,58 @@ use crate::syscall::{
     getxattr::{sys_fgetxattr, sys_getxattr, sys_lgetxattr},
     impl_syscall_nums_and_dispatch_fn,
     ioctl::sys_ioctl,
+    kcmp::sys_kcmp,
+    keyctl::sys_keyctl,
     kill::sys_kill,
+    landlock::{sys_landlock_add_rule, sys_landlock_create_ruleset, sys_landlock_restrict_self},
     link::{sys_link, sys_linkat},
     listen::sys_listen,
     listxattr::{sys_flistxattr, sys_listxattr, sys_llistxattr},
     lseek::sys_lseek,
     madvise::sys_madvise,
+    mincore::sys_mincore,
     mkdir::{sys_mkdir, sys_mkdirat},
     mknod::{sys_mknod, sys_mknodat},
+    mlock::{sys_mlock, sys_mlock2, sys_mlockall, sys_munlock, sys_munlockall},
     mmap::sys_mmap,
-    mount::sys_mount,
+    mount::{sys_fsmount, sys_mount, sys_move_mount},
     mprotect::sys_mprotect,
+    mq_getsetattr::sys_mq_getsetattr,
+    mq_notify::sys_mq_notify,
+    mq_open::sys_mq_open,
+    mq_timedreceive::sys_mq_timedreceive,
+    mq_timedsend::sys_mq_timedsend,
+    mq_unlink::sys_mq_unlink,
+    msgctl::sys_msgctl,
+    msgget::sys_msgget,
+    msgrcv::sys_msgrcv,
+    msgsnd::sys_msgsnd,
     msync::sys_msync,
     munmap::sys_munmap,
     nanosleep::{sys_clock_nanosleep, sys_nanosleep},
-    open::{sys_creat, sys_open, sys_openat},
+    open::{sys_creat, sys_open, sys_openat, sys_openat2},
     pause::sys_pause,
+    perf_event_open::sys_perf_event_open,
     pipe::{sys_pipe, sys_pipe2},
     poll::sys_poll,
     prctl::sys_prctl,
     pread64::sys_pread64,
     preadv::{sys_preadv, sys_preadv2, sys_readv},
     prlimit64::{sys_getrlimit, sys_prlimit64, sys_setrlimit},
+    process_vm::{sys_process_vm_readv, sys_process_vm_writev},
     pselect6::sys_pselect6,
     pwrite64::sys_pwrite64,
     pwritev::{sys_pwritev, sys_pwritev2, sys_writev},
     read::sys_read,
     readlink::{sys_readlink, sys_readlinkat},
+    reboot::sys_reboot,
     recvfrom::sys_recvfrom,
     recvmsg::sys_recvmsg,
     removexattr::{sys_fremovexattr, sys_lremovexattr, sys_removexattr},
-    rename::{sys_rename, sys_renameat},
+    rename::{sys_rename, sys_renameat, sys_renameat2},
+    request_key::sys_request_key,
     rmdir::sys_rmdir,
+    rseq::sys_rseq,
     rt_sigaction::sys_rt_sigaction,
     rt_sigpending::sys_rt_sigpending,
     rt_sigprocmask::sys_rt_sigprocmask,
@@ -102,6 +127,7 @@ use crate::syscall::{
     sched_setparam::sys_sched_setparam,
     sched_setscheduler::sys_sched_setscheduler,
     sched_yield::sys_sched_yield,
+    seccomp::sys_seccomp,
     select::sys_select,
     semctl::sys_semctl,
     semget::sys_semget,
@@ -126,26 +152,34 @@ use crate::syscall::{
     setsockopt::sys_setsockopt,
     setuid::sys_setuid,
     setxattr::{sys_fsetxattr, sys_lsetxattr, sys_setxattr},
+    shmat::sys_shmat,
+    shmctl::sys_shmctl,
+    shmdt::sys_shmdt,
+    shmget::sys_shmget,
     shutdown::sys_shutdown,
     sigaltstack::sys_sigaltstack,
     signalfd::{sys_signalfd, sys_signalfd4},
     socket::sys_socket,
     socketpair::sys_socketpair,
-    stat::{sys_fstat, sys_fstatat, sys_lstat, sys_stat},
+    splice::sys_splice,
+    stat::{sys_fstat, sys_fstatat, sys_lstat, sys_stat, sys_statx},
     statfs::{sys_fstatfs, sys_statfs},
     symlink::{sys_symlink, sys_symlinkat},
     sync::sys_sync,
     sysinfo::sys_sysinfo,
+    syslog::sys_syslog,
     tgkill::sys_tgkill,
     time::sys_time,
-    timer_create::{sys_timer_create, sys_timer_delete},
+    timer_create::{sys_timer_create, sys_timer_delete, sys_timer_getoverrun},
     timer_settime::{sys_timer_gettime, sys_timer_settime},
+    times::sys_times,
     truncate::{sys_ftruncate, sys_truncate},
     umask::sys_umask,
     umount::sys_umount,
     uname::sys_uname,
     unlink::{sys_unlink, sys_unlinkat},
     utimens::{sys_futimesat, sys_utime, sys_utimensat, sys_utimes},
+    vmsplice::sys_vmsplice,
     wait4::sys_wait4,
     waitid::sys_waitid,
     write::sys_write,
@@ -177,8 +211,12 @@ impl_syscall_nums_and_dispatch_fn! {
     SYS_PIPE = 22              => sys_pipe(args[..1]);
     SYS_SELECT = 23            => sys_select(args[..5]);
     SYS_MSYNC = 26             => sys_msync(args[..3]);
+    SYS_MINCORE = 27           => sys_mincore(args[..3]);
     SYS_SCHED_YIELD = 24       => sys_sched_yield(args[..0]);
     SYS_MADVISE = 28           => sys_madvise(args[..3]);
+    SYS_SHMGET = 29            => sys_shmget(args[..3]);
+    SYS_SHMAT = 30             => sys_shmat(args[..3]);
+    SYS_SHMCTL = 31            => sys_shmctl(args[..3]);
     SYS_DUP = 32               => sys_dup(args[..1]);
     SYS_DUP2 = 33              => sys_dup2(args[..2]);
     SYS_PAUSE = 34             => sys_pause(args[..0]);
@@ -214,6 +252,11 @@ impl_syscall_nums_and_dispatch_fn! {
     SYS_SEMGET = 64            => sys_semget(args[..3]);
     SYS_SEMOP = 65             => sys_semop(args[..3]);
     SYS_SEMCTL = 66            => sys_semctl(args[..4]);
+    SYS_SHMDT = 67             => sys_shmdt(args[..1]);
+    SYS_MSGGET = 68            => sys_msgget(args[..2]);
+    SYS_MSGSND = 69            => sys_msgsnd(args[..4]);
+    SYS_MSGRCV = 70            => sys_msgrcv(args[..5]);
+    SYS_MSGCTL = 71            => sys_msgctl(args[..3]);
     SYS_FCNTL = 72             => sys_fcntl(args[..3]);
     SYS_FLOCK = 73             => sys_flock(args[..2]);
     SYS_FSYNC = 74             => sys_fsync(args[..1]);
@@ -242,7 +285,9 @@ impl_syscall_nums_and_dispatch_fn! {
     SYS_GETRLIMIT = 97         => sys_getrlimit(args[..2]);
     SYS_GETRUSAGE = 98         => sys_getrusage(args[..2]);
     SYS_SYSINFO = 99           => sys_sysinfo(args[..1]);
+    SYS_TIMES = 100            => sys_times(args[..1]);
     SYS_GETUID = 102           => sys_getuid(args[..0]);
+    SYS_SYSLOG = 103           => sys_syslog(args[..3]);
     SYS_GETGID = 104           => sys_getgid(args[..0]);
     SYS_SETUID = 105           => sys_setuid(args[..1]);
     SYS_SETGID = 106           => sys_setgid(args[..1]);
@@ -281,13 +326,19 @@ impl_syscall_nums_and_dispatch_fn! {
     SYS_SCHED_GETSCHEDULER = 145 => sys_sched_getscheduler(args[..1]);
     SYS_SCHED_GET_PRIORITY_MAX = 146 => sys_sched_get_priority_max(args[..1]);
     SYS_SCHED_GET_PRIORITY_MIN = 147 => sys_sched_get_priority_min(args[..1]);
+    SYS_MLOCK = 149            => sys_mlock(args[..2]);
+    SYS_MUNLOCK = 150          => sys_munlock(args[..2]);
+    SYS_MLOCKALL = 151         => sys_mlockall(args[..1]);
+    SYS_MUNLOCKALL = 152       => sys_munlockall(args[..0]);
     SYS_PRCTL = 157            => sys_prctl(args[..5]);
     SYS_ARCH_PRCTL = 158       => sys_arch_prctl(args[..2], &mut user_ctx);
+    SYS_ADJTIMEX = 159         => sys_adjtimex(args[..1]);
     SYS_SETRLIMIT = 160        => sys_setrlimit(args[..2]);
     SYS_CHROOT = 161           => sys_chroot(args[..1]);
     SYS_SYNC = 162             => sys_sync(args[..0]);
     SYS_MOUNT = 165            => sys_mount(args[..5]);
     SYS_UMOUNT2 = 166           => sys_umount(args[..2]);
+    SYS_REBOOT = 169           => sys_reboot(args[..4]);
     SYS_GETTID = 186           => sys_gettid(args[..0]);
     SYS_SETXATTR = 188         => sys_setxattr(args[..5]);
     SYS_LSETXATTR = 189        => sys_lsetxattr(args[..5]);
@@ -312,6 +363,7 @@ impl_syscall_nums_and_dispatch_fn! {
     SYS_TIMER_CREATE = 222     => sys_timer_create(args[..3]);
     SYS_TIMER_SETTIME = 223    => sys_timer_settime(args[..4]);
     SYS_TIMER_GETTIME = 224    => sys_timer_gettime(args[..2]);
+    SYS_TIMER_GETOVERRUN = 225 => sys_timer_getoverrun(args[..1]);
     SYS_TIMER_DELETE = 226     => sys_timer_delete(args[..1]);
     SYS_CLOCK_GETTIME = 228    => sys_clock_gettime(args[..2]);
     SYS_CLOCK_NANOSLEEP = 230  => sys_clock_nanosleep(args[..4]);
@@ -320,7 +372,16 @@ impl_syscall_nums_and_dispatch_fn! {
     SYS_EPOLL_CTL = 233        => sys_epoll_ctl(args[..4]);
     SYS_TGKILL = 234           => sys_tgkill(args[..3]);
     SYS_UTIMES = 235           => sys_utimes(args[..2]);
+    SYS_MQ_OPEN = 240          => sys_mq_open(args[..4]);
+    SYS_MQ_UNLINK = 241        => sys_mq_unlink(args[..1]);
+    SYS_MQ_TIMEDSEND = 242     => sys_mq_timedsend(args[..5]);
+    SYS_MQ_TIMEDRECEIVE = 243  => sys_mq_timedreceive(args[..5]);
+    SYS_MQ_NOTIFY = 244        => sys_mq_notify(args[..2]);
+    SYS_MQ_GETSETATTR = 245    => sys_mq_getsetattr(args[..3]);
     SYS_WAITID = 247           => sys_waitid(args[..5]);
+    SYS_ADD_KEY = 248          => sys_add_key(args[..5]);
+    SYS_REQUEST_KEY = 249      => sys_request_key(args[..4]);
+    SYS_KEYCTL = 250           => sys_keyctl(args[..5]);
     SYS_OPENAT = 257           => sys_openat(args[..4]);
     SYS_MKDIRAT = 258          => sys_mkdirat(args[..3]);
     SYS_MKNODAT = 259          => sys_mknodat(args[..4]);
@@ -336,6 +397,8 @@ impl_syscall_nums_and_dispatch_fn! {
     SYS_FACCESSAT = 269        => sys_faccessat(args[..3]);
     SYS_PSELECT6 = 270         => sys_pselect6(args[..6]);
     SYS_SET_ROBUST_LIST = 273  => sys_set_robust_list(args[..2]);
+    SYS_SPLICE = 275           => sys_splice(args[..6]);
+    SYS_VMSPLICE = 278         => sys_vmsplice(args[..4]);
     SYS_UTIMENSAT = 280        => sys_utimensat(args[..4]);
     SYS_EPOLL_PWAIT = 281      => sys_epoll_pwait(args[..6]);
     SYS_SIGNALFD = 282         => sys_signalfd(args[..3]);
@@ -349,13 +412,33 @@ impl_syscall_nums_and_dispatch_fn! {
     SYS_PIPE2 = 293            => sys_pipe2(args[..2]);
     SYS_PREADV = 295           => sys_preadv(args[..4]);
     SYS_PWRITEV = 296          => sys_pwritev(args[..4]);
+    SYS_PERF_EVENT_OPEN = 298  => sys_perf_event_open(args[..5]);
     SYS_PRLIMIT64 = 302        => sys_prlimit64(args[..4]);
+    SYS_CLOCK_ADJTIME = 305    => sys_clock_adjtime(args[..2]);
     SYS_GETCPU = 309           => sys_getcpu(args[..3]);
+    SYS_PROCESS_VM_READV = 310 => sys_process_vm_readv(args[..6]);
+    SYS_PROCESS_VM_WRITEV = 311 => sys_process_vm_writev(args[..6]);
+    SYS_KCMP = 312             => sys_kcmp(args[..5]);
     SYS_SCHED_SETATTR = 314    => sys_sched_setattr(args[..3]);
     SYS_SCHED_GETATTR = 315    => sys_sched_getattr(args[..4]);
+    SYS_RENAMEAT2 = 316        => sys_renameat2(args[..5]);
+    SYS_SECCOMP = 317          => sys_seccomp(args[..3]);
     SYS_GETRANDOM = 318        => sys_getrandom(args[..3]);
+    SYS_BPF = 321              => sys_bpf(args[..3]);
     SYS_EXECVEAT = 322         => sys_execveat(args[..5], &mut user_ctx);
+    SYS_MLOCK2 = 325           => sys_mlock2(args[..3]);
+    SYS_COPY_FILE_RANGE = 326  => sys_copy_file_range(args[..6]);
     SYS_PREADV2 = 327          => sys_preadv2(args[..5]);
     SYS_PWRITEV2 = 328         => sys_pwritev2(args[..5]);
+    SYS_STATX = 332            => sys_statx(args[..5]);
+    SYS_RSEQ = 334             => sys_rseq(args[..4]);
+    SYS_MOVE_MOUNT = 429       => sys_move_mount(args[..5]);
+    SYS_FSOPEN = 430           => sys_fsopen(args[..2]);
+    SYS_FSCONFIG = 431         => sys_fsconfig(args[..5]);
+    SYS_FSMOUNT = 432          => sys_fsmount(args[..3]);
     SYS_CLONE3 = 435           => sys_clone3(args[..2], &user_ctx);
+    SYS_OPENAT2 = 437          => sys_openat2(args[..4]);
+    SYS_LANDLOCK_CREATE_RULESET = 444 => sys_landlock_create_ruleset(args[..3]);
+    SYS_LANDLOCK_ADD_RULE = 445 => sys_landlock_add_rule(args[..4]);
+    SYS_LANDLOCK_RESTRICT_SELF = 446 => sys_landlock_restrict_self(args[..2]);
 }
@@ -0,0 +1,334 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::format;
+use core::{any::Any, mem::size_of};
+
+use ostd::trace::tracepoints;
+
+use super::SyscallReturn;
+use crate::{
+    bpf::{
+        insn::BpfInsn, BpfLink, BpfMap, BpfMapType, BpfProg, BpfProgType, BpfUpdateFlag,
+        MAX_NR_INSNS,
+    },
+    fs::{
+        file_handle::FileLike,
+        file_table::{get_file_fast, FdFlags, FileDesc},
+    },
+    prelude::*,
+    process::credentials::capabilities::CapSet,
+};
+
+pub fn sys_bpf(cmd: i32, attr_addr: Vaddr, size: u32, ctx: &Context) -> Result<SyscallReturn> {
+    debug!(
+        "cmd = {}, attr_addr = {:#x}, size = {}",
+        cmd, attr_addr, size
+    );
+
+    let credentials = ctx.posix_thread.credentials();
+    let capset = credentials.effective_capset();
+    if !capset.contains(CapSet::BPF) && !capset.contains(CapSet::SYS_ADMIN) {
+        return_errno_with_message!(Errno::EPERM, "CAP_BPF is required to use eBPF");
+    }
+
+    let size = size as usize;
+    let ret = match cmd {
+        BPF_MAP_CREATE => map_create(read_attr(attr_addr, size, ctx)?, ctx)?,
+        BPF_MAP_LOOKUP_ELEM => map_lookup_elem(read_attr(attr_addr, size, ctx)?, ctx)?,
+        BPF_MAP_UPDATE_ELEM => map_update_elem(read_attr(attr_addr, size, ctx)?, ctx)?,
+        BPF_MAP_DELETE_ELEM => map_delete_elem(read_attr(attr_addr, size, ctx)?, ctx)?,
+        BPF_MAP_GET_NEXT_KEY => map_get_next_key(read_attr(attr_addr, size, ctx)?, ctx)?,
+        BPF_PROG_LOAD => prog_load(read_attr(attr_addr, size, ctx)?, ctx)?,
+        BPF_RAW_TRACEPOINT_OPEN => raw_tracepoint_open(read_attr(attr_addr, size, ctx)?, ctx)?,
+        BPF_LINK_CREATE => link_create(read_attr(attr_addr, size, ctx)?, ctx)?,
+        _ => return_errno_with_message!(Errno::EINVAL, "unsupported eBPF command"),
+    };
+
+    Ok(SyscallReturn::Return(ret as _))
+}
+
+fn map_create(attr: MapCreateAttr, ctx: &Context) -> Result<FileDesc> {
+    if attr.map_flags != 0 {
+        return_errno_with_message!(Errno::EINVAL, "unsupported map flags");
+    }
+
+    let map = BpfMap::new(
+        BpfMapType::try_from(attr.map_type)?,
+        attr.key_size as usize,
+        attr.value_size as usize,
+        attr.max_entries as usize,
+    )?;
+    insert_file(Arc::new(map), ctx)
+}
+
+fn map_lookup_elem(attr: MapElemAttr, ctx: &Context) -> Result<FileDesc> {
+    if attr.flags != 0 {
+        return_errno_with_message!(Errno::EINVAL, "unsupported lookup flags");
+    }
+
+    let map = get_bpf_file::<BpfMap>(attr.map_fd as FileDesc, ctx)?;
+    let key = read_bytes(attr.key as Vaddr, map.key_size(), ctx)?;
+    let value = map
+        .lookup(&key)
+        .ok_or_else(|| Error::with_message(Errno::ENOENT, "the element does not exist"))?;
+
+    let mut buf = vec![0u8; map.value_size()];
+    value.read(0, &mut buf).unwrap();
+    ctx.user_space()
+        .write_bytes(attr.value as Vaddr, &mut VmReader::from(buf.as_slice()))?;
+    Ok(0)
+}
+
+fn map_update_elem(attr: MapElemAttr, ctx: &Context) -> Result<FileDesc> {
+    let flag = BpfUpdateFlag::try_from(attr.flags)?;
+
+    let map = get_bpf_file::<BpfMap>(attr.map_fd as FileDesc, ctx)?;
+    let key = read_bytes(attr.key as Vaddr, map.key_size(), ctx)?;
+    let value = read_bytes(attr.value as Vaddr, map.value_size(), ctx)?;
+    map.update(&key, &value, flag)?;
+    Ok(0)
+}
+
+fn map_delete_elem(attr: MapElemAttr, ctx: &Context) -> Result<FileDesc> {
+    let map = get_bpf_file::<BpfMap>(attr.map_fd as FileDesc, ctx)?;
+    let key = read_bytes(attr.key as Vaddr, map.key_size(), ctx)?;
+    map.delete(&key)?;
+    Ok(0)
+}
+
+fn map_get_next_key(attr: MapElemAttr, ctx: &Context) -> Result<FileDesc> {
+    let map = get_bpf_file::<BpfMap>(attr.map_fd as FileDesc, ctx)?;
+    // A null key asks for the first key. The `value` field holds the address of the next key.
+    let key = if attr.key == 0 {
+        None
+    } else {
+        Some(read_bytes(attr.key as Vaddr, map.key_size(), ctx)?)
+    };
+    let next_key = map
+        .next_key(key.as_deref())
+        .ok_or_else(|| Error::with_message(Errno::ENOENT, "there are no more keys"))?;
+
+    ctx.user_space().write_bytes(
+        attr.value as Vaddr,
+        &mut VmReader::from(next_key.as_slice()),
+    )?;
+    Ok(0)
+}
+
+fn prog_load(attr: ProgLoadAttr, ctx: &Context) -> Result<FileDesc> {
+    if attr.prog_flags != 0 {
+        return_errno_with_message!(Errno::EINVAL, "unsupported program flags");
+    }
+    let prog_type = BpfProgType::from_user(attr.prog_type)?;
+
+    let nr_insns = attr.insn_cnt as usize;
+    if nr_insns == 0 || nr_insns > MAX_NR_INSNS {
+        return_errno_with_message!(Errno::E2BIG, "invalid number of instructions");
+    }
+    let bytes = read_bytes(attr.insns as Vaddr, nr_insns * size_of::<BpfInsn>(), ctx)?;
+    let insns = bytes
+        .chunks_exact(size_of::<BpfInsn>())
+        .map(BpfInsn::from_bytes)
+        .collect::<Vec<_>>();
+
+    let result = {
+        let file_table = ctx.thread_local.file_table().borrow();
+        let file_table_locked = file_table.read();
+        BpfProg::new(prog_type, insns, |fd| {
+            let file = file_table_locked.get_file(fd as FileDesc).ok()?.clone();
+            (file as Arc<dyn Any + Send + Sync>).downcast().ok()
+        })
+    };
+    let prog = match result {
+        Ok(prog) => prog,
+        Err(err) => {
+            // The reason of the rejection is written to the log buffer if there is one.
+            if attr.log_level != 0 && attr.log_buf != 0 && attr.log_size != 0 {
+                let mut log = format!("{}\n", err).into_bytes();
+                log.truncate(attr.log_size as usize - 1);
+                log.push(0);
+                ctx.user_space()
+                    .write_bytes(attr.log_buf as Vaddr, &mut VmReader::from(log.as_slice()))?;
+            }
+            return Err(err.into());
+        }
+    };
+
+    insert_file(Arc::new(prog), ctx)
+}
+
+fn raw_tracepoint_open(attr: RawTracepointAttr, ctx: &Context) -> Result<FileDesc> {
+    let prog = get_bpf_file::<BpfProg>(attr.prog_fd as FileDesc, ctx)?;
+    let name = ctx
+        .user_space()
+        .read_cstring(attr.name as Vaddr, MAX_TRACEPOINT_NAME_LEN)?;
+    let name = name
+        .to_str()
+        .map_err(|_| Error::with_message(Errno::EINVAL, "invalid tracepoint name"))?;
+    let tracepoint = tracepoints()
+        .into_iter()
+        .find(|tracepoint| tracepoint.name() == name)
+        .ok_or_else(|| Error::with_message(Errno::ENOENT, "the tracepoint does not exist"))?;
+
+    let link = BpfLink::attach_tracepoint(prog, tracepoint)?;
+    insert_file(Arc::new(link), ctx)
+}
+
+fn link_create(attr: LinkCreateAttr, ctx: &Context) -> Result<FileDesc> {
+    if attr.attach_type != BPF_TRACE_KPROBE_MULTI {
+        return_errno_with_message!(Errno::EINVAL, "unsupported attach type");
+    }
+    if attr.flags != 0 || attr.kprobe_multi.flags != 0 {
+        return_errno_with_message!(Errno::EINVAL, "unsupported link flags");
+    }
+
+    // Only one kprobe can be specified by its address or by its symbol name.
+    let multi = attr.kprobe_multi;
+    if multi.cnt != 1 || (multi.syms == 0) == (multi.addrs == 0) || multi.cookies != 0 {
+        return_errno_with_message!(Errno::EOPNOTSUPP, "only a single kprobe is supported");
+    }
+
+    let prog = get_bpf_file::<BpfProg>(attr.prog_fd as FileDesc, ctx)?;
+    let user_space = ctx.user_space();
+    let addr = if multi.addrs != 0 {
+        user_space.read_val::<u64>(multi.addrs as Vaddr)? as Vaddr
+    } else {
+        let name_addr = user_space.read_val::<u64>(multi.syms as Vaddr)?;
+        let name = user_space.read_cstring(name_addr as Vaddr, ostd::kallsyms::MAX_NAME_LEN + 1)?;
+        ostd::kallsyms::addr_of_symbol(name.to_str()?)
+            .ok_or_else(|| Error::with_message(Errno::ENOENT, "the symbol does not exist"))?
+    };
+
+    #[cfg(target_arch = "riscv64")]
+    {
+        let link = BpfLink::attach_kprobe(prog, addr)?;
+        insert_file(Arc::new(link), ctx)
+    }
+    #[cfg(not(target_arch = "riscv64"))]
+    {
+        let _ = (prog, addr);
+        return_errno_with_message!(Errno::EOPNOTSUPP, "kprobes are not supported");
+    }
+}
+
+/// Reads the attributes, which may be extended in the later versions of Linux.
+///
+/// Like Linux, the attributes that are not given are zeros, and the attributes that are not
+/// supported must be zeros.
+fn read_attr<T: Pod>(addr: Vaddr, size: usize, ctx: &Context) -> Result<T> {
+    if size > PAGE_SIZE {
+        return_errno_with_message!(Errno::E2BIG, "the attributes are too big");
+    }
+
+    let user_space = ctx.user_space();
+    let mut attr = T::new_zeroed();
+    let len = size.min(size_of::<T>());
+    user_space.read_bytes(addr, &mut VmWriter::from(&mut attr.as_bytes_mut()[..len]))?;
+
+    if size > size_of::<T>() {
+        let extension = read_bytes(addr + size_of::<T>(), size - size_of::<T>(), ctx)?;
+        if extension.iter().any(|&byte| byte != 0) {
+            return_errno_with_message!(Errno::E2BIG, "unknown attributes");
+        }
+    }
+
+    Ok(attr)
+}
+
+fn read_bytes(addr: Vaddr, len: usize, ctx: &Context) -> Result<Vec<u8>> {
+    let mut buf = vec![0u8; len];
+    ctx.user_space()
+        .read_bytes(addr, &mut VmWriter::from(buf.as_mut_slice()))?;
+    Ok(buf)
+}
+
+/// Gets the eBPF object referred to by the file descriptor.
+fn get_bpf_file<T: FileLike>(fd: FileDesc, ctx: &Context) -> Result<Arc<T>> {
+    let mut file_table = ctx.thread_local.file_table().borrow_mut();
+    let file = get_file_fast!(&mut file_table, fd).into_owned();
+    (file as Arc<dyn Any + Send + Sync>)
+        .downcast()
+        .map_err(|_| Error::with_message(Errno::EINVAL, "not the expected eBPF object"))
+}
+
+fn insert_file(file: Arc<dyn FileLike>, ctx: &Context) -> Result<FileDesc> {
+    let file_table = ctx.thread_local.file_table().borrow();
+    let mut file_table_locked = file_table.write();
+    file_table_locked.insert(file, FdFlags::CLOEXEC)
+}
+
+const BPF_MAP_CREATE: i32 = 0;
+const BPF_MAP_LOOKUP_ELEM: i32 = 1;
+const BPF_MAP_UPDATE_ELEM: i32 = 2;
+const BPF_MAP_DELETE_ELEM: i32 = 3;
+const BPF_MAP_GET_NEXT_KEY: i32 = 4;
+const BPF_PROG_LOAD: i32 = 5;
+const BPF_RAW_TRACEPOINT_OPEN: i32 = 17;
+const BPF_LINK_CREATE: i32 = 28;
+
+const BPF_TRACE_KPROBE_MULTI: u32 = 42;
+
+const MAX_TRACEPOINT_NAME_LEN: usize = 128;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct MapCreateAttr {
+    map_type: u32,
+    key_size: u32,
+    value_size: u32,
+    max_entries: u32,
+    map_flags: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct MapElemAttr {
+    map_fd: u32,
+    _pad: u32,
+    key: u64,
+    value: u64,
+    flags: u64,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct ProgLoadAttr {
+    prog_type: u32,
+    insn_cnt: u32,
+    insns: u64,
+    _license: u64,
+    log_level: u32,
+    log_size: u32,
+    log_buf: u64,
+    _kern_version: u32,
+    prog_flags: u32,
+    _prog_name: [u8; 16],
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct RawTracepointAttr {
+    name: u64,
+    prog_fd: u32,
+    _pad: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct LinkCreateAttr {
+    prog_fd: u32,
+    _target_fd: u32,
+    attach_type: u32,
+    flags: u32,
+    kprobe_multi: KprobeMultiAttr,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct KprobeMultiAttr {
+    flags: u32,
+    cnt: u32,
+    syms: u64,
+    addrs: u64,
+    cookies: u64,
+}
@@ -15,9 +15,9 @@ use crate::{
         clockid_t,
         clocks::{
             BootTimeClock, MonotonicClock, MonotonicCoarseClock, MonotonicRawClock, RealTimeClock,
-            RealTimeCoarseClock,
+            RealTimeCoarseClock, TaiClock,
         },
-        timespec_t, Clock,
+        compat_timespec_t, timespec_t, Clock,
     },
 };
 
@@ -36,6 +36,22 @@ pub fn sys_clock_gettime(
     Ok(SyscallReturn::Return(0))
 }
 
+/// The `clock_gettime` system call of 32-bit programs.
+pub fn compat_sys_clock_gettime(
+    clockid: clockid_t,
+    timespec_addr: Vaddr,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    debug!("clockid = {:?}", clockid);
+
+    let time_duration = read_clock(clockid, ctx)?;
+
+    let timespec = compat_timespec_t::from(time_duration);
+    ctx.user_space().write_val(timespec_addr, &timespec)?;
+
+    Ok(SyscallReturn::Return(0))
+}
+
 // The hard-coded clock IDs.
 #[derive(Debug, Copy, Clone, TryFromInt, PartialEq)]
 #[repr(i32)]
@@ -49,6 +65,9 @@ pub enum ClockId {
     CLOCK_REALTIME_COARSE = 5,
     CLOCK_MONOTONIC_COARSE = 6,
     CLOCK_BOOTTIME = 7,
+    CLOCK_REALTIME_ALARM = 8,
+    CLOCK_BOOTTIME_ALARM = 9,
+    CLOCK_TAI = 11,
 }
 
 /// The information decoded from a dynamic clock ID.
@@ -121,6 +140,11 @@ pub fn read_clock(clockid: clockid_t, ctx: &Context) -> Result<Duration> {
             ClockId::CLOCK_REALTIME_COARSE => Ok(RealTimeCoarseClock::get().read_time()),
             ClockId::CLOCK_MONOTONIC_COARSE => Ok(MonotonicCoarseClock::get().read_time()),
             ClockId::CLOCK_BOOTTIME => Ok(BootTimeClock::get().read_time()),
+            // The alarm clocks are the same as the clocks they are based on, except that their
+            // timers can wake up the system from suspension.
+            ClockId::CLOCK_REALTIME_ALARM => Ok(RealTimeClock::get().read_time()),
+            ClockId::CLOCK_BOOTTIME_ALARM => Ok(BootTimeClock::get().read_time()),
+            ClockId::CLOCK_TAI => Ok(TaiClock::get().read_time()),
             ClockId::CLOCK_PROCESS_CPUTIME_ID => Ok(ctx.process.prof_clock().read_time()),
             ClockId::CLOCK_THREAD_CPUTIME_ID => Ok(ctx.posix_thread.prof_clock().read_time()),
         }
@@ -131,9 +155,13 @@ pub fn read_clock(clockid: clockid_t, ctx: &Context) -> Result<Duration> {
                 let process = process_table::get_process(pid)
                     .ok_or_else(|| crate::Error::with_message(Errno::EINVAL, "invalid clock ID"))?;
                 match clock_type {
-                    DynamicClockType::Profiling => Ok(process.prof_clock().read_time()),
+                    // The scheduling clock is the same as the profiling clock, since the CPU
+                    // time is accounted precisely.
+                    DynamicClockType::Profiling | DynamicClockType::Scheduling => {
+                        Ok(process.prof_clock().read_time())
+                    }
                     DynamicClockType::Virtual => Ok(process.prof_clock().user_clock().read_time()),
-                    // TODO: support scheduling clock and fd clock.
+                    // TODO: support fd clock.
                     _ => unimplemented!(),
                 }
             }
@@ -142,7 +170,9 @@ pub fn read_clock(clockid: clockid_t, ctx: &Context) -> Result<Duration> {
                     .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid clock ID"))?;
                 let posix_thread = thread.as_posix_thread().unwrap();
                 match clock_type {
-                    DynamicClockType::Profiling => Ok(posix_thread.prof_clock().read_time()),
+                    DynamicClockType::Profiling | DynamicClockType::Scheduling => {
+                        Ok(posix_thread.prof_clock().read_time())
+                    }
                     DynamicClockType::Virtual => {
                         Ok(posix_thread.prof_clock().user_clock().read_time())
                     }
@@ -4,6 +4,7 @@ use super::SyscallReturn;
 use crate::{
     fs::file_table::{get_file_fast, FileDesc},
     prelude::*,
+    security::hooks,
     util::net::read_socket_addr_from_user,
 };
 
@@ -20,6 +21,7 @@ pub fn sys_connect(
     let file = get_file_fast!(&mut file_table, sockfd);
     let socket = file.as_socket_or_err()?;
 
+    hooks::socket_connect(socket, &socket_addr)?;
     socket
         .connect(socket_addr)
         .map_err(|err| match err.error() {
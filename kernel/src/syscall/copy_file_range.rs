@@ -0,0 +1,78 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{
+    splice::{
+        check_readable, check_writable, copy_between_files, get_file_pair, read_offset_from_user,
+        write_offset_to_user, MAX_COUNT,
+    },
+    SyscallReturn,
+};
+use crate::{
+    fs::{
+        file_handle::FileLike,
+        file_table::FileDesc,
+        utils::{InodeType, StatusFlags},
+    },
+    prelude::*,
+};
+
+pub fn sys_copy_file_range(
+    fd_in: FileDesc,
+    off_in_ptr: Vaddr,
+    fd_out: FileDesc,
+    off_out_ptr: Vaddr,
+    len: usize,
+    flags: u32,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let mut off_in = read_offset_from_user(off_in_ptr, ctx)?;
+    let mut off_out = read_offset_from_user(off_out_ptr, ctx)?;
+    debug!(
+        "fd_in = {}, off_in = {:?}, fd_out = {}, off_out = {:?}, len = 0x{:x}, flags = 0x{:x}",
+        fd_in, off_in, fd_out, off_out, len, flags
+    );
+
+    if flags != 0 {
+        return_errno_with_message!(Errno::EINVAL, "the flags must be zero");
+    }
+
+    let (in_file, out_file) = get_file_pair(fd_in, fd_out, ctx)?;
+    check_readable(&in_file)?;
+    check_writable(&out_file)?;
+    if out_file.status_flags().contains(StatusFlags::O_APPEND) {
+        return_errno_with_message!(Errno::EBADF, "the output file is append-only");
+    }
+    check_regular_file(&in_file)?;
+    check_regular_file(&out_file)?;
+
+    let len = len.min(MAX_COUNT);
+    if len == 0 {
+        return Ok(SyscallReturn::Return(0));
+    }
+
+    let in_handle = in_file.as_inode_or_err()?;
+    let out_handle = out_file.as_inode_or_err()?;
+    if Arc::ptr_eq(in_handle.dentry().inode(), out_handle.dentry().inode()) {
+        let in_start = off_in.unwrap_or_else(|| in_handle.offset());
+        let out_start = off_out.unwrap_or_else(|| out_handle.offset());
+        if in_start < out_start.saturating_add(len) && out_start < in_start.saturating_add(len) {
+            return_errno_with_message!(Errno::EINVAL, "the ranges of the same file overlap");
+        }
+    }
+
+    let copied_len =
+        copy_between_files(&in_file, off_in.as_mut(), &out_file, off_out.as_mut(), len)?;
+
+    write_offset_to_user(off_in_ptr, off_in, ctx)?;
+    write_offset_to_user(off_out_ptr, off_out, ctx)?;
+
+    Ok(SyscallReturn::Return(copied_len as _))
+}
+
+fn check_regular_file(file: &Arc<dyn FileLike>) -> Result<()> {
+    match file.metadata().type_ {
+        InodeType::File => Ok(()),
+        InodeType::Dir => return_errno_with_message!(Errno::EISDIR, "the file is a directory"),
+        _ => return_errno_with_message!(Errno::EINVAL, "the file is not a regular file"),
+    }
+}
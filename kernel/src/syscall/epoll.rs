@@ -42,7 +42,7 @@ pub fn sys_epoll_create1(flags: u32, ctx: &Context) -> Result<SyscallReturn> {
 
     let epoll_file: Arc<EpollFile> = EpollFile::new();
     let file_table = ctx.thread_local.file_table().borrow();
-    let fd = file_table.write().insert(epoll_file, fd_flags);
+    let fd = file_table.write().insert(epoll_file, fd_flags)?;
     Ok(SyscallReturn::Return(fd as _))
 }
 
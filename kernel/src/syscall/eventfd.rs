@@ -46,12 +46,12 @@ pub fn sys_eventfd2(init_val: u64, flags: u32, ctx: &Context) -> Result<SyscallR
         .ok_or_else(|| Error::with_message(Errno::EINVAL, "unknown flags"))?;
     debug!("init_val = 0x{:x}, flags = {:?}", init_val, flags);
 
-    let fd = do_sys_eventfd2(init_val, flags, ctx);
+    let fd = do_sys_eventfd2(init_val, flags, ctx)?;
 
     Ok(SyscallReturn::Return(fd as _))
 }
 
-fn do_sys_eventfd2(init_val: u64, flags: Flags, ctx: &Context) -> FileDesc {
+fn do_sys_eventfd2(init_val: u64, flags: Flags, ctx: &Context) -> Result<FileDesc> {
     let event_file = EventFile::new(init_val, flags);
     let fd = {
         let file_table = ctx.thread_local.file_table().borrow();
@@ -61,9 +61,9 @@ fn do_sys_eventfd2(init_val: u64, flags: Flags, ctx: &Context) -> FileDesc {
         } else {
             FdFlags::empty()
         };
-        file_table_locked.insert(Arc::new(event_file), fd_flags)
+        file_table_locked.insert(Arc::new(event_file), fd_flags)?
     };
-    fd
+    Ok(fd)
 }
 
 bitflags! {
@@ -247,6 +247,7 @@ impl FileLike for EventFile {
             atime: now,
             mtime: now,
             ctime: now,
+            btime: None,
             type_: InodeType::NamedPipe,
             mode: InodeMode::from_bits_truncate(0o200),
             nlinks: 1,
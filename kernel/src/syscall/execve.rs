@@ -8,11 +8,13 @@ use ostd::{
 
 use super::{constants::*, SyscallReturn};
 use crate::{
+    cpu::LinuxAbi,
     fs::{
         file_table::{get_file_fast, FileDesc},
         fs_resolver::{FsPath, AT_FDCWD},
-        path::Dentry,
+        path::{Dentry, PerMountFlags},
     },
+    perf,
     prelude::*,
     process::{
         check_executable_file, posix_thread::ThreadName, renew_vm_and_map, Credentials, Process,
@@ -95,8 +97,10 @@ fn do_execve(
     } = ctx;
 
     let executable_path = elf_file.abs_path();
-    let argv = read_cstring_vec(argv_ptr_ptr, MAX_ARGV_NUMBER, MAX_ARG_LEN, ctx)?;
-    let envp = read_cstring_vec(envp_ptr_ptr, MAX_ENVP_NUMBER, MAX_ENV_LEN, ctx)?;
+    // The pointers are of 32 bits if the calling program is a 32-bit program.
+    let is_compat = user_context.is_compat();
+    let argv = read_cstring_vec(argv_ptr_ptr, MAX_ARGV_NUMBER, MAX_ARG_LEN, is_compat, ctx)?;
+    let envp = read_cstring_vec(envp_ptr_ptr, MAX_ENVP_NUMBER, MAX_ENV_LEN, is_compat, ctx)?;
     debug!(
         "filename: {:?}, argv = {:?}, envp = {:?}",
         executable_path, argv, envp
@@ -120,7 +124,7 @@ fn do_execve(
     debug!("load program to root vmar");
     let fs_resolver = &*posix_thread.fs().resolver().read();
     let program_to_load =
-        ProgramToLoad::build_from_file(elf_file.clone(), fs_resolver, argv, envp, 1)?;
+        ProgramToLoad::build_from_file(elf_file.clone(), fs_resolver, argv, envp)?;
 
     let process_vm = process.vm();
     if process.status().is_vfork_child() {
@@ -137,18 +141,32 @@ fn do_execve(
         process_vm.clear_and_map();
     }
 
+    // The credentials are updated before the program is loaded, since they are reported to the
+    // new program in the auxiliary vector.
+    let no_new_privs = posix_thread.credentials().no_new_privs();
+    let credentials = posix_thread.credentials_mut();
+    set_uid_from_elf(process, &credentials, &elf_file, no_new_privs)?;
+    set_gid_from_elf(process, &credentials, &elf_file, no_new_privs)?;
+    credentials.set_keep_capabilities(false);
+
     let (new_executable_path, elf_load_info) =
-        program_to_load.load_to_vm(process_vm, fs_resolver)?;
+        program_to_load.load_to_vm(process_vm, fs_resolver, &posix_thread.credentials())?;
 
     // After the program has been successfully loaded, the virtual memory of the current process
     // is initialized. Hence, it is necessary to clear the previously recorded robust list.
     *thread_local.robust_list().borrow_mut() = None;
     debug!("load elf in execve succeeds");
 
-    let credentials = posix_thread.credentials_mut();
-    set_uid_from_elf(process, &credentials, &elf_file)?;
-    set_gid_from_elf(process, &credentials, &elf_file)?;
-    credentials.set_keep_capabilities(false);
+    // The new program does not inherit the thread keyring and the process keyring.
+    posix_thread.keyrings().on_exec();
+    // The POSIX timers are not preserved across `execve`.
+    process.timer_manager().clear_posix_timers();
+    // The rseq area is in the old address space.
+    thread_local.rseq().clear();
+    // Syscall user dispatch refers to the code of the old program.
+    thread_local.syscall_user_dispatch().disable();
+    // The perf events with `enable_on_exec` start counting in the new program.
+    perf::on_exec(ctx);
 
     // set executable path
     process.set_executable_path(new_executable_path);
@@ -157,6 +175,7 @@ fn do_execve(
     // set cpu context to default
     *user_context.general_regs_mut() = RawGeneralRegs::default();
     user_context.set_tls_pointer(0);
+    user_context.set_compat(elf_load_info.is_compat());
     *user_context.fpu_state_mut() = FpuState::default();
     // FIXME: how to reset the FPU state correctly? Before returning to the user space,
     // the kernel will call `handle_pending_signal`, which may update the CPU states so that
@@ -190,6 +209,7 @@ fn read_cstring_vec(
     array_ptr: Vaddr,
     max_string_number: usize,
     max_string_len: usize,
+    is_compat: bool,
     ctx: &Context,
 ) -> Result<Vec<CString>> {
     let mut res = Vec::new();
@@ -201,8 +221,15 @@ fn read_cstring_vec(
     let mut find_null = false;
     let user_space = ctx.user_space();
     for _ in 0..max_string_number {
-        let cstring_ptr = user_space.read_val::<usize>(read_addr)?;
-        read_addr += 8;
+        let cstring_ptr = if is_compat {
+            let ptr = user_space.read_val::<u32>(read_addr)?;
+            read_addr += size_of::<u32>();
+            ptr as Vaddr
+        } else {
+            let ptr = user_space.read_val::<usize>(read_addr)?;
+            read_addr += size_of::<usize>();
+            ptr
+        };
         // read a null pointer
         if cstring_ptr == 0 {
             find_null = true;
@@ -222,8 +249,9 @@ fn set_uid_from_elf(
     current: &Process,
     credentials: &Credentials<WriteOp>,
     elf_file: &Dentry,
+    no_new_privs: bool,
 ) -> Result<()> {
-    if elf_file.mode()?.has_set_uid() {
+    if elf_file.mode()?.has_set_uid() && !is_nosuid_mount(elf_file) && !no_new_privs {
         let uid = elf_file.owner()?;
         credentials.set_euid(uid);
 
@@ -240,8 +268,9 @@ fn set_gid_from_elf(
     current: &Process,
     credentials: &Credentials<WriteOp>,
     elf_file: &Dentry,
+    no_new_privs: bool,
 ) -> Result<()> {
-    if elf_file.mode()?.has_set_gid() {
+    if elf_file.mode()?.has_set_gid() && !is_nosuid_mount(elf_file) && !no_new_privs {
         let gid = elf_file.group()?;
         credentials.set_egid(gid);
 
@@ -252,3 +281,11 @@ fn set_gid_from_elf(
     credentials.reset_sgid();
     Ok(())
 }
+
+/// Returns whether the set-user-ID and set-group-ID bits are ignored by the mount.
+fn is_nosuid_mount(elf_file: &Dentry) -> bool {
+    elf_file
+        .mount_node()
+        .flags()
+        .contains(PerMountFlags::NOSUID)
+}
@@ -5,12 +5,13 @@ use crate::{
     fs::{
         file_handle::FileLike,
         file_table::{get_file_fast, FdFlags, FileDesc, WithFileTable},
+        pipe::{pipe_max_size, round_pipe_size, PipeReader, PipeWriter},
         utils::{
             FileRange, RangeLockItem, RangeLockItemBuilder, RangeLockType, StatusFlags, OFFSET_MAX,
         },
     },
     prelude::*,
-    process::{process_table, Pid},
+    process::{credentials::capabilities::CapSet, process_table, Pid, ResourceType},
 };
 
 pub fn sys_fcntl(fd: FileDesc, cmd: i32, arg: u64, ctx: &Context) -> Result<SyscallReturn> {
@@ -31,10 +32,21 @@ pub fn sys_fcntl(fd: FileDesc, cmd: i32, arg: u64, ctx: &Context) -> Result<Sysc
         }),
         FcntlCmd::F_GETOWN => handle_getown(fd, ctx),
         FcntlCmd::F_SETOWN => handle_setown(fd, arg, ctx),
+        FcntlCmd::F_SETPIPE_SZ => handle_setpipe_sz(fd, arg, ctx),
+        FcntlCmd::F_GETPIPE_SZ => handle_getpipe_sz(fd, ctx),
     }
 }
 
 fn handle_dupfd(fd: FileDesc, arg: u64, flags: FdFlags, ctx: &Context) -> Result<SyscallReturn> {
+    let rlimit_nofile = ctx
+        .process
+        .resource_limits()
+        .get_rlimit(ResourceType::RLIMIT_NOFILE)
+        .get_cur();
+    if arg >= rlimit_nofile {
+        return_errno_with_message!(Errno::EINVAL, "the fd exceeds the file limit");
+    }
+
     let file_table = ctx.thread_local.file_table().borrow();
     let new_fd = file_table.write().dup(fd, arg as FileDesc, flags)?;
     Ok(SyscallReturn::Return(new_fd as _))
@@ -159,6 +171,52 @@ fn handle_setown(fd: FileDesc, arg: u64, ctx: &Context) -> Result<SyscallReturn>
     Ok(SyscallReturn::Return(0))
 }
 
+fn handle_setpipe_sz(fd: FileDesc, arg: u64, ctx: &Context) -> Result<SyscallReturn> {
+    let capacity = match usize::try_from(arg) {
+        Ok(size) => round_pipe_size(size)?,
+        Err(_) => return_errno_with_message!(Errno::EINVAL, "the pipe size is too large"),
+    };
+
+    let mut file_table = ctx.thread_local.file_table().borrow_mut();
+    let file = get_file_fast!(&mut file_table, fd);
+    let check_limit = |old_capacity: usize| {
+        if capacity > old_capacity && capacity > pipe_max_size() {
+            let credentials = ctx.posix_thread.credentials();
+            if !credentials
+                .effective_capset()
+                .contains(CapSet::SYS_RESOURCE)
+            {
+                return_errno_with_message!(Errno::EPERM, "the pipe size exceeds the limit");
+            }
+        }
+        Ok(())
+    };
+    if let Some(reader) = file.downcast_ref::<PipeReader>() {
+        check_limit(reader.capacity())?;
+        reader.set_capacity(capacity)?;
+    } else if let Some(writer) = file.downcast_ref::<PipeWriter>() {
+        check_limit(writer.capacity())?;
+        writer.set_capacity(capacity)?;
+    } else {
+        return_errno_with_message!(Errno::EBADF, "the file is not a pipe");
+    }
+
+    Ok(SyscallReturn::Return(capacity as _))
+}
+
+fn handle_getpipe_sz(fd: FileDesc, ctx: &Context) -> Result<SyscallReturn> {
+    let mut file_table = ctx.thread_local.file_table().borrow_mut();
+    let file = get_file_fast!(&mut file_table, fd);
+    let capacity = if let Some(reader) = file.downcast_ref::<PipeReader>() {
+        reader.capacity()
+    } else if let Some(writer) = file.downcast_ref::<PipeWriter>() {
+        writer.capacity()
+    } else {
+        return_errno_with_message!(Errno::EBADF, "the file is not a pipe");
+    };
+    Ok(SyscallReturn::Return(capacity as _))
+}
+
 #[repr(i32)]
 #[derive(Debug, Clone, Copy, TryFromInt)]
 #[expect(non_camel_case_types)]
@@ -174,6 +232,8 @@ enum FcntlCmd {
     F_SETOWN = 8,
     F_GETOWN = 9,
     F_DUPFD_CLOEXEC = 1030,
+    F_SETPIPE_SZ = 1031,
+    F_GETPIPE_SZ = 1032,
 }
 
 #[expect(non_camel_case_types)]
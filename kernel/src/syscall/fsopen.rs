@@ -0,0 +1,262 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! `fsopen()` creates a "file system context" (we name it as `FsContext`)
+//! which collects the parameters of a new file system.
+//!
+//! The parameters are set by `fsconfig()`, which also creates the file system
+//! with the `FSCONFIG_CMD_CREATE` command. Then the file system can be attached
+//! to a detached mount by `fsmount()` and be moved into the mount tree by
+//! `move_mount()`.
+//!
+//! For more detailed information about these syscalls,
+//! refer to the man 2 fsopen and man 2 fsconfig documentation.
+//!
+
+use alloc::format;
+
+use super::SyscallReturn;
+use crate::{
+    events::IoEvents,
+    fs::{
+        file_handle::FileLike,
+        file_table::{get_file_fast, FdFlags, FileDesc},
+        utils::{FileSystem, InodeMode, InodeType, Metadata},
+    },
+    prelude::*,
+    process::{
+        signal::{PollHandle, Pollable},
+        Gid, Uid,
+    },
+    syscall::constants::MAX_FILENAME_LEN,
+    time::clocks::RealTimeClock,
+};
+
+pub fn sys_fsopen(fs_name_addr: Vaddr, flags: u32, ctx: &Context) -> Result<SyscallReturn> {
+    let fs_name = ctx
+        .user_space()
+        .read_cstring(fs_name_addr, MAX_FILENAME_LEN)?;
+    let flags = FsOpenFlags::from_bits(flags)
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "unknown flags"))?;
+    debug!("fs_name = {:?}, flags = {:?}", fs_name, flags);
+
+    if fs_name.is_empty() {
+        return_errno_with_message!(Errno::EINVAL, "fs_name is empty");
+    }
+
+    let fs_context = FsContext::new(fs_name);
+    let fd = {
+        let file_table = ctx.thread_local.file_table().borrow();
+        let mut file_table_locked = file_table.write();
+        let fd_flags = if flags.contains(FsOpenFlags::FSOPEN_CLOEXEC) {
+            FdFlags::CLOEXEC
+        } else {
+            FdFlags::empty()
+        };
+        file_table_locked.insert(Arc::new(fs_context), fd_flags)?
+    };
+
+    Ok(SyscallReturn::Return(fd as _))
+}
+
+pub fn sys_fsconfig(
+    fd: FileDesc,
+    cmd: u32,
+    key_addr: Vaddr,
+    value_addr: Vaddr,
+    aux: i32,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let cmd = FsConfigCmd::try_from(cmd)?;
+    debug!(
+        "fd = {}, cmd = {:?}, key_addr = 0x{:x}, value_addr = 0x{:x}, aux = {}",
+        fd, cmd, key_addr, value_addr, aux
+    );
+
+    let mut file_table = ctx.thread_local.file_table().borrow_mut();
+    let file = get_file_fast!(&mut file_table, fd).into_owned();
+    // Drop `file_table` as creating a file system may look up paths.
+    drop(file_table);
+
+    let fs_context = file
+        .downcast_ref::<FsContext>()
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "not a file system context"))?;
+
+    let user_space = ctx.user_space();
+    let read_key = || {
+        if key_addr == 0 {
+            return_errno_with_message!(Errno::EINVAL, "the key is required");
+        }
+        let key = user_space.read_cstring(key_addr, MAX_FILENAME_LEN)?;
+        if key.is_empty() {
+            return_errno_with_message!(Errno::EINVAL, "the key is empty");
+        }
+        Ok(key.to_string_lossy().into_owned())
+    };
+
+    match cmd {
+        FsConfigCmd::SetFlag => {
+            if value_addr != 0 || aux != 0 {
+                return_errno_with_message!(Errno::EINVAL, "a flag does not take a value");
+            }
+            fs_context.set_option(read_key()?, None)?;
+        }
+        FsConfigCmd::SetString => {
+            if value_addr == 0 || aux != 0 {
+                return_errno_with_message!(Errno::EINVAL, "the string value is required");
+            }
+            let key = read_key()?;
+            let value = user_space.read_cstring(value_addr, PAGE_SIZE)?;
+            fs_context.set_option(key, Some(value))?;
+        }
+        FsConfigCmd::CmdCreate | FsConfigCmd::CmdCreateExcl => {
+            if key_addr != 0 || value_addr != 0 || aux != 0 {
+                return_errno_with_message!(Errno::EINVAL, "a command does not take arguments");
+            }
+            fs_context.create(ctx)?;
+        }
+        FsConfigCmd::SetBinary
+        | FsConfigCmd::SetPath
+        | FsConfigCmd::SetPathEmpty
+        | FsConfigCmd::SetFd
+        | FsConfigCmd::CmdReconfigure => {
+            return_errno_with_message!(Errno::EOPNOTSUPP, "the command is not supported");
+        }
+    }
+
+    Ok(SyscallReturn::Return(0))
+}
+
+bitflags! {
+    struct FsOpenFlags: u32 {
+        const FSOPEN_CLOEXEC = 1 << 0;
+    }
+}
+
+#[derive(Debug, Clone, Copy, TryFromInt)]
+#[repr(u32)]
+enum FsConfigCmd {
+    SetFlag = 0,
+    SetString = 1,
+    SetBinary = 2,
+    SetPath = 3,
+    SetPathEmpty = 4,
+    SetFd = 5,
+    CmdCreate = 6,
+    CmdReconfigure = 7,
+    CmdCreateExcl = 8,
+}
+
+/// A file system context, which is opened by `fsopen()`.
+pub(super) struct FsContext {
+    fs_type: CString,
+    phase: Mutex<FsContextPhase>,
+}
+
+enum FsContextPhase {
+    /// The parameters are being collected.
+    Collecting {
+        source: Option<CString>,
+        options: Vec<String>,
+    },
+    /// The file system is created and waiting to be mounted.
+    Created(Arc<dyn FileSystem>),
+    /// The file system has been taken by `fsmount()`.
+    Mounted,
+}
+
+impl FsContext {
+    fn new(fs_type: CString) -> Self {
+        Self {
+            fs_type,
+            phase: Mutex::new(FsContextPhase::Collecting {
+                source: None,
+                options: Vec::new(),
+            }),
+        }
+    }
+
+    /// Sets an option, where the special `source` option names the device.
+    fn set_option(&self, key: String, value: Option<CString>) -> Result<()> {
+        let mut phase = self.phase.lock();
+        let FsContextPhase::Collecting { source, options } = &mut *phase else {
+            return_errno_with_message!(Errno::EBUSY, "the file system has been created");
+        };
+
+        match value {
+            Some(value) if key == "source" => {
+                if source.is_some() {
+                    return_errno_with_message!(Errno::EINVAL, "the source is already set");
+                }
+                *source = Some(value);
+            }
+            Some(value) => options.push(format!("{}={}", key, value.to_string_lossy())),
+            None => options.push(key),
+        }
+        Ok(())
+    }
+
+    fn create(&self, ctx: &Context) -> Result<()> {
+        let mut phase = self.phase.lock();
+        let FsContextPhase::Collecting { source, options } = &*phase else {
+            return_errno_with_message!(Errno::EBUSY, "the file system has been created");
+        };
+
+        let devname = source.clone().unwrap_or_default();
+        let data = CString::new(options.join(","))
+            .map_err(|_| Error::with_message(Errno::EINVAL, "invalid options"))?;
+        let fs = super::mount::get_fs(self.fs_type.clone(), devname, Some(data), ctx)?;
+        *phase = FsContextPhase::Created(fs);
+        Ok(())
+    }
+
+    /// Takes the created file system, which can be mounted only once.
+    pub(super) fn take_fs(&self) -> Result<Arc<dyn FileSystem>> {
+        let mut phase = self.phase.lock();
+        match core::mem::replace(&mut *phase, FsContextPhase::Mounted) {
+            FsContextPhase::Created(fs) => Ok(fs),
+            FsContextPhase::Mounted => {
+                return_errno_with_message!(Errno::EBUSY, "the file system has been mounted")
+            }
+            collecting => {
+                *phase = collecting;
+                return_errno_with_message!(Errno::EINVAL, "the file system is not created")
+            }
+        }
+    }
+}
+
+impl Pollable for FsContext {
+    fn poll(&self, _mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
+        IoEvents::empty()
+    }
+}
+
+impl FileLike for FsContext {
+    fn read(&self, _writer: &mut VmWriter) -> Result<usize> {
+        // The error messages of the file system are not recorded.
+        return_errno_with_message!(Errno::ENODATA, "no error messages");
+    }
+
+    fn metadata(&self) -> Metadata {
+        // This is a dummy implementation.
+        // TODO: Add "anonymous inode fs" and link `FsContext` to it.
+        let now = RealTimeClock::get().read_time();
+        Metadata {
+            dev: 0,
+            ino: 0,
+            size: 0,
+            blk_size: 0,
+            blocks: 0,
+            atime: now,
+            mtime: now,
+            ctime: now,
+            btime: None,
+            type_: InodeType::File,
+            mode: InodeMode::from_bits_truncate(0o600),
+            nlinks: 1,
+            uid: Uid::new_root(),
+            gid: Gid::new_root(),
+            rdev: 0,
+        }
+    }
+}
@@ -84,15 +84,11 @@ pub fn sys_futex(
         )))
     };
 
-    let pid = if futex_flags.contains(FutexFlags::FUTEX_PRIVATE) {
-        Some(ctx.process.pid())
-    } else {
-        None
-    };
+    let is_private = futex_flags.contains(FutexFlags::FUTEX_PRIVATE);
     let res = match futex_op {
         FutexOp::FUTEX_WAIT => {
             let timeout = get_futex_timeout(utime_addr)?;
-            futex_wait(futex_addr as _, futex_val as _, timeout, ctx, pid).map(|_| 0)
+            futex_wait(futex_addr as _, futex_val as _, timeout, ctx, is_private).map(|_| 0)
         }
         FutexOp::FUTEX_WAIT_BITSET => {
             let timeout = get_futex_timeout(utime_addr)?;
@@ -102,7 +98,7 @@ pub fn sys_futex(
                 timeout,
                 bitset as _,
                 ctx,
-                pid,
+                is_private,
             )
             .map(|_| 0)
         }
@@ -110,13 +106,13 @@ pub fn sys_futex(
             // From gVisor/test/syscalls/linux/futex.cc:260: "The Linux kernel wakes one waiter even if val is 0 or negative."
             // To be consistent with Linux, we set the max_count to 1 if it is 0 or negative.
             let max_count = (futex_val as i32).max(1) as usize;
-            futex_wake(futex_addr as _, max_count, pid).map(|count| count as isize)
+            futex_wake(futex_addr as _, max_count, is_private).map(|count| count as isize)
         }
         FutexOp::FUTEX_WAKE_BITSET => {
             // From gVisor/test/syscalls/linux/futex.cc:260: "The Linux kernel wakes one waiter even if val is 0 or negative."
             // To be consistent with Linux, we set the max_count to 1 if it is 0 or negative.
             let max_count = (futex_val as i32).max(1) as usize;
-            futex_wake_bitset(futex_addr as _, max_count, bitset as _, pid)
+            futex_wake_bitset(futex_addr as _, max_count, bitset as _, is_private)
                 .map(|count| count as isize)
         }
         FutexOp::FUTEX_REQUEUE => {
@@ -127,7 +123,7 @@ pub fn sys_futex(
                 max_nwakes,
                 max_nrequeues,
                 futex_new_addr as _,
-                pid,
+                is_private,
             )
             .map(|nwakes| nwakes as _)
         }
@@ -15,13 +15,14 @@ pub fn sys_getcpu(cpu: Vaddr, node: Vaddr, _tcache: Vaddr, ctx: &Context) -> Res
         cpuid.as_usize(),
         ostd::cpu::num_cpus()
     );
-    // Since cpu and node can be NULL, we need to check them before writing
+    // Since cpu and node can be NULL, we need to check them before writing.
+    // Both of them point to `unsigned int`, so writing a `usize` would corrupt the user memory.
     if cpu != 0 {
         ctx.user_space()
-            .write_val::<usize>(cpu, &cpuid.as_usize())?;
+            .write_val::<u32>(cpu, &(cpuid.as_usize() as u32))?;
     }
     if node != 0 {
-        ctx.user_space().write_val::<usize>(node, &0)?; // TODO: NUMA is not supported
+        ctx.user_space().write_val::<u32>(node, &0)?; // TODO: NUMA is not supported
     }
     Ok(SyscallReturn::Return(0))
 }
@@ -1,9 +1,14 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use core::time::Duration;
+
 use int_to_c_enum::TryFromInt;
 
 use super::SyscallReturn;
-use crate::{prelude::*, time::timeval_t};
+use crate::{
+    prelude::*,
+    time::{clocks::ProfClock, timeval_t},
+};
 
 #[derive(Debug, Copy, Clone, TryFromInt, PartialEq)]
 #[repr(i32)]
@@ -24,26 +29,13 @@ pub fn sys_getrusage(target: i32, rusage_addr: Vaddr, ctx: &Context) -> Result<S
 
     if rusage_addr != 0 {
         let rusage = match rusage_target {
-            RusageTarget::ForSelf => {
-                let process = ctx.process;
-                rusage_t {
-                    ru_utime: process.prof_clock().user_clock().read_time().into(),
-                    ru_stime: process.prof_clock().kernel_clock().read_time().into(),
-                    ..Default::default()
-                }
-            }
-            RusageTarget::Thread => {
-                let posix_thread = ctx.posix_thread;
-                rusage_t {
-                    ru_utime: posix_thread.prof_clock().user_clock().read_time().into(),
-                    ru_stime: posix_thread.prof_clock().kernel_clock().read_time().into(),
-                    ..Default::default()
-                }
+            RusageTarget::ForSelf => rusage_t::from_prof_clocks(&[ctx.process.prof_clock()]),
+            RusageTarget::Children => {
+                rusage_t::from_prof_clocks(&[ctx.process.children_prof_clock()])
             }
-            // To support `Children` and `Both` we need to implement the functionality to
-            // accumulate the resources of a child process back to the parent process
-            // upon the child's termination.
-            _ => {
+            RusageTarget::Thread => rusage_t::from_prof_clocks(&[ctx.posix_thread.prof_clock()]),
+            // Like Linux, `RUSAGE_BOTH` is only used internally by the kernel.
+            RusageTarget::Both => {
                 return_errno_with_message!(Errno::EINVAL, "the target type is not supported")
             }
         };
@@ -90,3 +82,22 @@ pub struct rusage_t {
     /// involuntary
     pub ru_nivcsw: u64,
 }
+
+impl rusage_t {
+    /// Creates a `rusage_t` with the sum of the user and system CPU time of the profiling clocks.
+    pub fn from_prof_clocks(prof_clocks: &[&Arc<ProfClock>]) -> Self {
+        let user_time = prof_clocks
+            .iter()
+            .map(|prof_clock| prof_clock.user_clock().read_time())
+            .sum::<Duration>();
+        let kernel_time = prof_clocks
+            .iter()
+            .map(|prof_clock| prof_clock.kernel_clock().read_time())
+            .sum::<Duration>();
+        Self {
+            ru_utime: user_time.into(),
+            ru_stime: kernel_time.into(),
+            ..Default::default()
+        }
+    }
+}
@@ -30,6 +30,7 @@ pub fn sys_getsockopt(
     let socket = file.as_socket_or_err()?;
 
     let mut raw_option = new_raw_socket_option(level, optname)?;
+    raw_option.read_input_from_user(optval, optlen)?;
     debug!("raw option: {:?}", raw_option);
 
     socket.get_option(raw_option.as_sock_option_mut())?;
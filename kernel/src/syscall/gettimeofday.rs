@@ -3,7 +3,7 @@
 use super::SyscallReturn;
 use crate::{
     prelude::*,
-    time::{timeval_t, SystemTime},
+    time::{compat_timeval_t, timeval_t, SystemTime},
 };
 
 // The use of the timezone structure is obsolete.
@@ -25,3 +25,19 @@ pub fn sys_gettimeofday(
 
     Ok(SyscallReturn::Return(0))
 }
+
+/// The `gettimeofday` system call of 32-bit programs.
+pub fn compat_sys_gettimeofday(timeval_addr: Vaddr, ctx: &Context) -> Result<SyscallReturn> {
+    if timeval_addr == 0 {
+        return Ok(SyscallReturn::Return(0));
+    }
+
+    let time_val = {
+        let now = SystemTime::now();
+        let time_duration = now.duration_since(&SystemTime::UNIX_EPOCH)?;
+        compat_timeval_t::from(time_duration)
+    };
+    ctx.user_space().write_val(timeval_addr, &time_val)?;
+
+    Ok(SyscallReturn::Return(0))
+}
@@ -10,7 +10,18 @@ use crate::{
 };
 
 pub fn sys_ioctl(fd: FileDesc, cmd: u32, arg: Vaddr, ctx: &Context) -> Result<SyscallReturn> {
-    let ioctl_cmd = IoctlCmd::try_from(cmd)?;
+    let Ok(ioctl_cmd) = IoctlCmd::try_from(cmd) else {
+        debug!(
+            "fd = {}, raw ioctl_cmd = 0x{:x}, arg = 0x{:x}",
+            fd, cmd, arg
+        );
+        let file = {
+            let mut file_table = ctx.thread_local.file_table().borrow_mut();
+            get_file_fast!(&mut file_table, fd).into_owned()
+        };
+        let res = file.ioctl_raw(cmd, arg)?;
+        return Ok(SyscallReturn::Return(res as _));
+    };
     debug!(
         "fd = {}, ioctl_cmd = {:?}, arg = 0x{:x}",
         fd, ioctl_cmd, arg
@@ -0,0 +1,122 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use spin::Once;
+
+use super::SyscallReturn;
+use crate::{
+    fs::file_table::FileDesc,
+    prelude::*,
+    process::{
+        check_ptrace_access,
+        posix_thread::{thread_table, AsPosixThread, PosixThread},
+    },
+    thread::{Thread, Tid},
+    util::random::getrandom,
+};
+
+pub fn sys_kcmp(
+    tid1: Tid,
+    tid2: Tid,
+    type_: u32,
+    idx1: u64,
+    idx2: u64,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    debug!(
+        "tid1 = {}, tid2 = {}, type = {}, idx1 = {}, idx2 = {}",
+        tid1, tid2, type_, idx1, idx2
+    );
+
+    let thread1 = get_accessible_thread(tid1, ctx)?;
+    let thread2 = get_accessible_thread(tid2, ctx)?;
+    let posix_thread1 = thread1.as_posix_thread().unwrap();
+    let posix_thread2 = thread2.as_posix_thread().unwrap();
+
+    let type_ = KcmpType::try_from(type_)?;
+    let (ptr1, ptr2) = match type_ {
+        KcmpType::File => (
+            file_ptr(posix_thread1, idx1 as FileDesc)?,
+            file_ptr(posix_thread2, idx2 as FileDesc)?,
+        ),
+        KcmpType::Vm => (
+            ptr_of(posix_thread1.process().vm()),
+            ptr_of(posix_thread2.process().vm()),
+        ),
+        KcmpType::Files => (
+            ptr_of(&*posix_thread1.file_table().read()),
+            ptr_of(&*posix_thread2.file_table().read()),
+        ),
+        KcmpType::Fs => (ptr_of(&**posix_thread1.fs()), ptr_of(&**posix_thread2.fs())),
+        KcmpType::Sighand => (
+            ptr_of(&**posix_thread1.process().sig_dispositions()),
+            ptr_of(&**posix_thread2.process().sig_dispositions()),
+        ),
+        KcmpType::Io | KcmpType::Sysvsem | KcmpType::EpollTfd => {
+            return_errno_with_message!(Errno::EOPNOTSUPP, "the resource type is not supported");
+        }
+    };
+
+    // Like Linux, the pointers are obfuscated, so that only their equality is meaningful, and
+    // their order can be used to sort the resources.
+    let (key1, key2) = (obfuscate(ptr1, type_), obfuscate(ptr2, type_));
+    let res = match key1.cmp(&key2) {
+        core::cmp::Ordering::Equal => 0,
+        core::cmp::Ordering::Less => 1,
+        core::cmp::Ordering::Greater => 2,
+    };
+    Ok(SyscallReturn::Return(res))
+}
+
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromInt)]
+enum KcmpType {
+    File = 0,
+    Vm = 1,
+    Files = 2,
+    Fs = 3,
+    Sighand = 4,
+    Io = 5,
+    Sysvsem = 6,
+    EpollTfd = 7,
+}
+
+/// Gets the thread, which the current thread must be allowed to attach to with `ptrace`.
+fn get_accessible_thread(tid: Tid, ctx: &Context) -> Result<Arc<Thread>> {
+    let thread = thread_table::get_thread(tid)
+        .ok_or_else(|| Error::with_message(Errno::ESRCH, "the thread does not exist"))?;
+    let process = thread.as_posix_thread().unwrap().process();
+    check_ptrace_access(&process, ctx)?;
+    Ok(thread)
+}
+
+fn file_ptr(posix_thread: &PosixThread, fd: FileDesc) -> Result<usize> {
+    let file_table = posix_thread.file_table().read();
+    let file = file_table.get_file(fd)?;
+    Ok(Arc::as_ptr(file) as *const () as usize)
+}
+
+fn ptr_of<T>(resource: &T) -> usize {
+    resource as *const T as usize
+}
+
+/// The number of the types of the resources.
+const NR_KCMP_TYPES: usize = 8;
+
+fn obfuscate(ptr: usize, type_: KcmpType) -> u64 {
+    static COOKIES: Once<[[u64; 2]; NR_KCMP_TYPES]> = Once::new();
+
+    let cookies = COOKIES.call_once(|| {
+        let mut cookies = [[0u64; 2]; NR_KCMP_TYPES];
+        for cookie in cookies.iter_mut() {
+            let mut bytes = [0u8; 16];
+            getrandom(&mut bytes).unwrap();
+            cookie[0] = u64::from_ne_bytes(bytes[..8].try_into().unwrap());
+            // The multiplier is odd, so the mapping is a bijection.
+            cookie[1] = u64::from_ne_bytes(bytes[8..].try_into().unwrap()) | 1;
+        }
+        cookies
+    });
+
+    let [xor, mul] = cookies[type_ as usize];
+    (ptr as u64 ^ xor).wrapping_mul(mul)
+}
@@ -0,0 +1,83 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{kexec_load::check_boot_capability, SyscallReturn};
+use crate::{
+    fs::file_table::{get_file_fast, FileDesc},
+    kexec,
+    prelude::*,
+};
+
+/// The maximum length of the kernel command line, including the trailing `\0`.
+const COMMAND_LINE_SIZE: usize = 1024;
+
+pub fn sys_kexec_file_load(
+    kernel_fd: FileDesc,
+    initrd_fd: FileDesc,
+    cmdline_len: usize,
+    cmdline_addr: Vaddr,
+    flags: u64,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    debug!(
+        "kernel_fd = {}, initrd_fd = {}, cmdline_len = {}, cmdline_addr = {:#x}, flags = {:#x}",
+        kernel_fd, initrd_fd, cmdline_len, cmdline_addr, flags
+    );
+
+    check_boot_capability(ctx)?;
+
+    let flags = u32::try_from(flags)
+        .ok()
+        .and_then(KexecFileFlags::from_bits)
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid flags"))?;
+    let is_crash = flags.contains(KexecFileFlags::KEXEC_FILE_ON_CRASH);
+    if flags.contains(KexecFileFlags::KEXEC_FILE_UNLOAD) {
+        kexec::unload_image(is_crash)?;
+        return Ok(SyscallReturn::Return(0));
+    }
+
+    let kernel = read_file(kernel_fd, ctx)?;
+    let initrd = if flags.contains(KexecFileFlags::KEXEC_FILE_NO_INITRAMFS) {
+        None
+    } else {
+        Some(read_file(initrd_fd, ctx)?)
+    };
+    let cmdline = read_cmdline(cmdline_addr, cmdline_len, ctx)?;
+
+    kexec::load_file_image(&kernel, initrd.as_deref(), &cmdline, is_crash)?;
+    Ok(SyscallReturn::Return(0))
+}
+
+fn read_file(fd: FileDesc, ctx: &Context) -> Result<Vec<u8>> {
+    let mut file_table = ctx.thread_local.file_table().borrow_mut();
+    let file = get_file_fast!(&mut file_table, fd);
+    let mut data = Vec::new();
+    file.as_inode_or_err()?.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+fn read_cmdline(addr: Vaddr, len: usize, ctx: &Context) -> Result<String> {
+    if len == 0 {
+        return Ok(String::new());
+    }
+    if len > COMMAND_LINE_SIZE {
+        return_errno_with_message!(Errno::EINVAL, "the command line is too long");
+    }
+
+    let mut cmdline = vec![0u8; len];
+    ctx.user_space()
+        .read_bytes(addr, &mut VmWriter::from(cmdline.as_mut_slice()))?;
+    if cmdline.pop() != Some(0) {
+        return_errno_with_message!(Errno::EINVAL, "the command line is not terminated");
+    }
+    String::from_utf8(cmdline)
+        .map_err(|_| Error::with_message(Errno::EINVAL, "the command line is not valid UTF-8"))
+}
+
+bitflags! {
+    struct KexecFileFlags: u32 {
+        const KEXEC_FILE_UNLOAD = 0x0000_0001;
+        const KEXEC_FILE_ON_CRASH = 0x0000_0002;
+        const KEXEC_FILE_NO_INITRAMFS = 0x0000_0004;
+        const KEXEC_FILE_DEBUG = 0x0000_0008;
+    }
+}
@@ -0,0 +1,105 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::ops::Range;
+
+use ostd::{
+    arch::kexec::{crash::EM_RISCV, KEXEC_SEGMENT_MAX},
+    mm::Paddr,
+};
+
+use super::SyscallReturn;
+use crate::{kexec, prelude::*, process::credentials::capabilities::CapSet};
+
+pub fn sys_kexec_load(
+    entry: Paddr,
+    nr_segments: usize,
+    segments_addr: Vaddr,
+    flags: u64,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    debug!(
+        "entry = {:#x}, nr_segments = {}, segments_addr = {:#x}, flags = {:#x}",
+        entry, nr_segments, segments_addr, flags
+    );
+
+    check_boot_capability(ctx)?;
+
+    let arch = flags & KEXEC_ARCH_MASK;
+    if arch != KEXEC_ARCH_DEFAULT && arch != (EM_RISCV as u64) << 16 {
+        return_errno_with_message!(Errno::EINVAL, "the architecture is not supported");
+    }
+    let flags = KexecFlags::from_bits((flags & !KEXEC_ARCH_MASK) as u32)
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid flags"))?;
+    if flags.contains(KexecFlags::KEXEC_PRESERVE_CONTEXT) {
+        return_errno_with_message!(Errno::EINVAL, "preserving the context is not supported");
+    }
+    let is_crash = flags.contains(KexecFlags::KEXEC_ON_CRASH);
+
+    if nr_segments > KEXEC_SEGMENT_MAX {
+        return_errno_with_message!(Errno::EINVAL, "too many segments");
+    }
+    if nr_segments == 0 {
+        kexec::unload_image(is_crash)?;
+        return Ok(SyscallReturn::Return(0));
+    }
+
+    let user_space = ctx.user_space();
+    let mut segments = Vec::with_capacity(nr_segments);
+    for i in 0..nr_segments {
+        let segment: KexecSegment =
+            user_space.read_val(segments_addr + i * size_of::<KexecSegment>())?;
+        segments.push(segment.to_ranges()?);
+    }
+
+    kexec::load_image(entry, &segments, is_crash, ctx)?;
+    Ok(SyscallReturn::Return(0))
+}
+
+pub(super) fn check_boot_capability(ctx: &Context) -> Result<()> {
+    if !ctx
+        .posix_thread
+        .credentials()
+        .effective_capset()
+        .contains(CapSet::SYS_BOOT)
+    {
+        return_errno_with_message!(Errno::EPERM, "`CAP_SYS_BOOT` is required");
+    }
+    Ok(())
+}
+
+const KEXEC_ARCH_MASK: u64 = 0xffff_0000;
+const KEXEC_ARCH_DEFAULT: u64 = 0;
+
+bitflags! {
+    struct KexecFlags: u32 {
+        const KEXEC_ON_CRASH = 0x0000_0001;
+        const KEXEC_PRESERVE_CONTEXT = 0x0000_0002;
+    }
+}
+
+/// The segment passed by the user space.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/kexec.h#L55>
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct KexecSegment {
+    buf: Vaddr,
+    bufsz: usize,
+    mem: Paddr,
+    memsz: usize,
+}
+
+impl KexecSegment {
+    fn to_ranges(self) -> Result<(Range<Vaddr>, Range<Paddr>)> {
+        if self.bufsz > self.memsz {
+            return_errno_with_message!(Errno::EINVAL, "the buffer is larger than the memory");
+        }
+        let (Some(buf_end), Some(mem_end)) = (
+            self.buf.checked_add(self.bufsz),
+            self.mem.checked_add(self.memsz),
+        ) else {
+            return_errno_with_message!(Errno::EINVAL, "the segment overflows");
+        };
+        Ok((self.buf..buf_end, self.mem..mem_end))
+    }
+}
@@ -0,0 +1,189 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{
+    add_key::{read_description, read_key_type, read_payload},
+    SyscallReturn,
+};
+use crate::{
+    prelude::*,
+    process::{Gid, Uid},
+    security::keys::{join_session_keyring, lookup_key, KeyLookupFlags, KeyPerm, KeySerial},
+};
+
+pub fn sys_keyctl(
+    cmd: i32,
+    arg2: u64,
+    arg3: u64,
+    arg4: u64,
+    arg5: u64,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let cmd = KeyctlCmd::try_from(cmd).map_err(|_| {
+        Error::with_message(Errno::EOPNOTSUPP, "the keyctl command is not supported")
+    })?;
+    debug!(
+        "cmd = {:?}, args = [{:#x}, {:#x}, {:#x}, {:#x}]",
+        cmd, arg2, arg3, arg4, arg5
+    );
+
+    let posix_thread = ctx.posix_thread;
+    let credentials = posix_thread.credentials();
+    let user_space = ctx.user_space();
+
+    let ret = match cmd {
+        KeyctlCmd::KEYCTL_GET_KEYRING_ID => {
+            let flags = if arg3 != 0 {
+                KeyLookupFlags::CREATE
+            } else {
+                KeyLookupFlags::empty()
+            };
+            let key = lookup_key(posix_thread, arg2 as KeySerial, flags)?;
+            key.check_permission(&credentials, KeyPerm::SEARCH)?;
+            key.key().serial() as isize
+        }
+        KeyctlCmd::KEYCTL_JOIN_SESSION_KEYRING => {
+            let name = if arg2 != 0 {
+                Some(read_description(&user_space, arg2 as Vaddr)?)
+            } else {
+                None
+            };
+            join_session_keyring(posix_thread, name.as_deref())?.serial() as isize
+        }
+        KeyctlCmd::KEYCTL_UPDATE => {
+            let payload = read_payload(&user_space, arg3 as Vaddr, arg4 as usize)?;
+            let key = lookup_key(posix_thread, arg2 as KeySerial, KeyLookupFlags::CREATE)?;
+            key.check_permission(&credentials, KeyPerm::WRITE)?;
+            key.key().update(payload)?;
+            0
+        }
+        KeyctlCmd::KEYCTL_REVOKE => {
+            let key = lookup_key(posix_thread, arg2 as KeySerial, KeyLookupFlags::empty())?;
+            key.check_permission(&credentials, KeyPerm::WRITE)
+                .or_else(|_| key.check_permission(&credentials, KeyPerm::SETATTR))?;
+            key.key().revoke();
+            0
+        }
+        KeyctlCmd::KEYCTL_CHOWN => {
+            let key = lookup_key(posix_thread, arg2 as KeySerial, KeyLookupFlags::PARTIAL)?;
+            key.check_permission(&credentials, KeyPerm::SETATTR)?;
+            // `-1` means that the owner or the group is not changed.
+            let uid = (arg3 as u32 != u32::MAX).then(|| Uid::new(arg3 as u32));
+            let gid = (arg4 as u32 != u32::MAX).then(|| Gid::new(arg4 as u32));
+            key.key().chown(&credentials, uid, gid)?;
+            0
+        }
+        KeyctlCmd::KEYCTL_SETPERM => {
+            let key = lookup_key(
+                posix_thread,
+                arg2 as KeySerial,
+                KeyLookupFlags::CREATE | KeyLookupFlags::PARTIAL,
+            )?;
+            key.check_permission(&credentials, KeyPerm::SETATTR)?;
+            key.key().set_perm(&credentials, arg3 as u32)?;
+            0
+        }
+        KeyctlCmd::KEYCTL_DESCRIBE => {
+            let key = lookup_key(posix_thread, arg2 as KeySerial, KeyLookupFlags::PARTIAL)?;
+            key.check_permission(&credentials, KeyPerm::VIEW)?;
+
+            let mut description = key.key().describe().into_bytes();
+            description.push(0);
+            // The description is copied only if the whole description fits in the buffer.
+            if arg3 != 0 && arg4 as usize >= description.len() {
+                user_space
+                    .write_bytes(arg3 as Vaddr, &mut VmReader::from(description.as_slice()))?;
+            }
+            description.len() as isize
+        }
+        KeyctlCmd::KEYCTL_CLEAR => {
+            let keyring = lookup_key(posix_thread, arg2 as KeySerial, KeyLookupFlags::CREATE)?;
+            keyring.check_permission(&credentials, KeyPerm::WRITE)?;
+            keyring.key().clear()?;
+            0
+        }
+        KeyctlCmd::KEYCTL_LINK => {
+            let keyring = lookup_key(posix_thread, arg3 as KeySerial, KeyLookupFlags::CREATE)?;
+            keyring.check_permission(&credentials, KeyPerm::WRITE)?;
+            let key = lookup_key(posix_thread, arg2 as KeySerial, KeyLookupFlags::CREATE)?;
+            key.check_permission(&credentials, KeyPerm::LINK)?;
+            keyring.key().link(key.key().clone())?;
+            0
+        }
+        KeyctlCmd::KEYCTL_UNLINK => {
+            let keyring = lookup_key(posix_thread, arg3 as KeySerial, KeyLookupFlags::empty())?;
+            keyring.check_permission(&credentials, KeyPerm::WRITE)?;
+            let key = lookup_key(posix_thread, arg2 as KeySerial, KeyLookupFlags::PARTIAL)?;
+            keyring.key().unlink(key.key())?;
+            0
+        }
+        KeyctlCmd::KEYCTL_SEARCH => {
+            let key_type = read_key_type(&user_space, arg3 as Vaddr)?;
+            let description = read_description(&user_space, arg4 as Vaddr)?;
+
+            let keyring = lookup_key(posix_thread, arg2 as KeySerial, KeyLookupFlags::empty())?;
+            keyring.check_permission(&credentials, KeyPerm::SEARCH)?;
+            let dest_keyring = if arg5 != 0 {
+                let dest = lookup_key(posix_thread, arg5 as KeySerial, KeyLookupFlags::CREATE)?;
+                dest.check_permission(&credentials, KeyPerm::WRITE)?;
+                Some(dest)
+            } else {
+                None
+            };
+
+            let key = keyring
+                .search(&credentials, key_type, &description)
+                .ok_or_else(|| Error::with_message(Errno::ENOKEY, "the key is not found"))?;
+            if let Some(dest_keyring) = dest_keyring {
+                key.check_permission(&credentials, KeyPerm::LINK)?;
+                dest_keyring.key().link(key.key().clone())?;
+            }
+            key.key().serial() as isize
+        }
+        KeyctlCmd::KEYCTL_READ => {
+            let key = lookup_key(posix_thread, arg2 as KeySerial, KeyLookupFlags::empty())?;
+            // A possessed key can be read if it can be searched.
+            key.check_permission(&credentials, KeyPerm::READ)
+                .or_else(|err| {
+                    if key.is_possessed() {
+                        key.check_permission(&credentials, KeyPerm::SEARCH)
+                    } else {
+                        Err(err)
+                    }
+                })?;
+
+            let payload = key.key().read()?;
+            if arg3 != 0 {
+                let copy_len = payload.len().min(arg4 as usize);
+                user_space.write_bytes(arg3 as Vaddr, &mut VmReader::from(&payload[..copy_len]))?;
+            }
+            payload.len() as isize
+        }
+        KeyctlCmd::KEYCTL_INVALIDATE => {
+            let key = lookup_key(posix_thread, arg2 as KeySerial, KeyLookupFlags::PARTIAL)?;
+            key.check_permission(&credentials, KeyPerm::SEARCH)?;
+            key.key().invalidate();
+            0
+        }
+    };
+
+    Ok(SyscallReturn::Return(ret))
+}
+
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, TryFromInt)]
+#[expect(non_camel_case_types)]
+enum KeyctlCmd {
+    KEYCTL_GET_KEYRING_ID = 0,
+    KEYCTL_JOIN_SESSION_KEYRING = 1,
+    KEYCTL_UPDATE = 2,
+    KEYCTL_REVOKE = 3,
+    KEYCTL_CHOWN = 4,
+    KEYCTL_SETPERM = 5,
+    KEYCTL_DESCRIBE = 6,
+    KEYCTL_CLEAR = 7,
+    KEYCTL_LINK = 8,
+    KEYCTL_UNLINK = 9,
+    KEYCTL_SEARCH = 10,
+    KEYCTL_READ = 11,
+    KEYCTL_INVALIDATE = 21,
+}
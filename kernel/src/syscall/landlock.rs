@@ -0,0 +1,167 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::mem::size_of;
+
+use super::SyscallReturn;
+use crate::{
+    fs::file_table::{get_file_fast, FdFlags, FileDesc},
+    prelude::*,
+    process::credentials::capabilities::CapSet,
+    security::landlock::{AccessFs, LandlockDomain, LandlockRuleset, LANDLOCK_ABI_VERSION},
+};
+
+pub fn sys_landlock_create_ruleset(
+    attr_addr: Vaddr,
+    size: usize,
+    flags: u32,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    debug!(
+        "attr_addr = {:#x}, size = {}, flags = {:#x}",
+        attr_addr, size, flags
+    );
+
+    if flags == LANDLOCK_CREATE_RULESET_VERSION {
+        if attr_addr != 0 || size != 0 {
+            return_errno_with_message!(Errno::EINVAL, "the ruleset attributes are not expected");
+        }
+        return Ok(SyscallReturn::Return(LANDLOCK_ABI_VERSION as _));
+    }
+    if flags != 0 {
+        return_errno_with_message!(Errno::EINVAL, "invalid flags");
+    }
+
+    let attr = read_ruleset_attr(attr_addr, size, ctx)?;
+    let handled_access = AccessFs::from_bits(attr.handled_access_fs)
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid handled access"))?;
+    let ruleset = LandlockRuleset::new(handled_access)?;
+
+    let fd = {
+        let file_table = ctx.thread_local.file_table().borrow();
+        let mut file_table_locked = file_table.write();
+        file_table_locked.insert(Arc::new(ruleset), FdFlags::CLOEXEC)?
+    };
+    Ok(SyscallReturn::Return(fd as _))
+}
+
+pub fn sys_landlock_add_rule(
+    ruleset_fd: FileDesc,
+    rule_type: u32,
+    rule_attr_addr: Vaddr,
+    flags: u32,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    debug!(
+        "ruleset_fd = {}, rule_type = {}, rule_attr_addr = {:#x}, flags = {:#x}",
+        ruleset_fd, rule_type, rule_attr_addr, flags
+    );
+
+    if flags != 0 {
+        return_errno_with_message!(Errno::EINVAL, "invalid flags");
+    }
+
+    let mut file_table = ctx.thread_local.file_table().borrow_mut();
+    let ruleset_file = get_file_fast!(&mut file_table, ruleset_fd).into_owned();
+    let ruleset = ruleset_file
+        .downcast_ref::<LandlockRuleset>()
+        .ok_or_else(|| Error::with_message(Errno::EBADFD, "not a Landlock ruleset"))?;
+
+    if rule_type != LANDLOCK_RULE_PATH_BENEATH {
+        return_errno_with_message!(Errno::EINVAL, "invalid rule type");
+    }
+    let attr = ctx
+        .user_space()
+        .read_val::<PathBeneathAttr>(rule_attr_addr)?;
+    let allowed_access = AccessFs::from_bits(attr.allowed_access)
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid allowed access"))?;
+
+    let parent_file = get_file_fast!(&mut file_table, attr.parent_fd);
+    let dentry = parent_file
+        .as_inode_or_err()
+        .map_err(|_| Error::with_message(Errno::EBADFD, "the rule is not beneath a file"))?
+        .dentry()
+        .clone();
+    ruleset.add_path_beneath_rule(dentry, allowed_access)?;
+
+    Ok(SyscallReturn::Return(0))
+}
+
+pub fn sys_landlock_restrict_self(
+    ruleset_fd: FileDesc,
+    flags: u32,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    debug!("ruleset_fd = {}, flags = {:#x}", ruleset_fd, flags);
+
+    if flags != 0 {
+        return_errno_with_message!(Errno::EINVAL, "invalid flags");
+    }
+
+    // A thread that is not privileged cannot pass its restrictions to a privileged program,
+    // which may be abused to confuse the program.
+    let credentials = ctx.posix_thread.credentials();
+    if !credentials.no_new_privs() && !credentials.effective_capset().contains(CapSet::SYS_ADMIN) {
+        return_errno_with_message!(
+            Errno::EPERM,
+            "no_new_privs must be set to enforce a Landlock ruleset"
+        );
+    }
+
+    let mut file_table = ctx.thread_local.file_table().borrow_mut();
+    let ruleset_file = get_file_fast!(&mut file_table, ruleset_fd);
+    let ruleset = ruleset_file
+        .downcast_ref::<LandlockRuleset>()
+        .ok_or_else(|| Error::with_message(Errno::EBADFD, "not a Landlock ruleset"))?;
+
+    let domain = match credentials.landlock_domain() {
+        Some(domain) => domain.with_ruleset(ruleset)?,
+        None => LandlockDomain::default().with_ruleset(ruleset)?,
+    };
+    ctx.posix_thread
+        .credentials_mut()
+        .set_landlock_domain(Arc::new(domain));
+
+    Ok(SyscallReturn::Return(0))
+}
+
+/// Reads the ruleset attributes, which may be extended in the later Landlock ABI versions.
+fn read_ruleset_attr(addr: Vaddr, size: usize, ctx: &Context) -> Result<RulesetAttr> {
+    if size < size_of::<RulesetAttr>() {
+        return_errno_with_message!(Errno::EINVAL, "the ruleset attributes are too small");
+    }
+    if size > PAGE_SIZE {
+        return_errno_with_message!(Errno::E2BIG, "the ruleset attributes are too big");
+    }
+
+    let user_space = ctx.user_space();
+    let attr = user_space.read_val::<RulesetAttr>(addr)?;
+
+    // The attributes of the later versions must be zeros.
+    let mut extension = vec![0u8; size - size_of::<RulesetAttr>()];
+    user_space.read_bytes(
+        addr + size_of::<RulesetAttr>(),
+        &mut VmWriter::from(extension.as_mut_slice()),
+    )?;
+    if extension.iter().any(|&byte| byte != 0) {
+        return_errno_with_message!(Errno::E2BIG, "unknown ruleset attributes");
+    }
+
+    Ok(attr)
+}
+
+const LANDLOCK_CREATE_RULESET_VERSION: u32 = 1 << 0;
+
+const LANDLOCK_RULE_PATH_BENEATH: u32 = 1;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct RulesetAttr {
+    handled_access_fs: u64,
+}
+
+#[repr(C, packed)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct PathBeneathAttr {
+    allowed_access: u64,
+    parent_fd: i32,
+}
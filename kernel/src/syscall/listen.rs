@@ -4,6 +4,7 @@ use super::SyscallReturn;
 use crate::{
     fs::file_table::{get_file_fast, FileDesc},
     prelude::*,
+    security::hooks,
 };
 
 pub fn sys_listen(sockfd: FileDesc, backlog: i32, ctx: &Context) -> Result<SyscallReturn> {
@@ -13,6 +14,7 @@ pub fn sys_listen(sockfd: FileDesc, backlog: i32, ctx: &Context) -> Result<Sysca
     let file = get_file_fast!(&mut file_table, sockfd);
     let socket = file.as_socket_or_err()?;
 
+    hooks::socket_listen(socket, backlog as usize)?;
     socket.listen(backlog as usize)?;
 
     Ok(SyscallReturn::Return(0))
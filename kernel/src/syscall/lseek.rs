@@ -10,6 +10,26 @@ use crate::{
 };
 
 pub fn sys_lseek(fd: FileDesc, offset: isize, whence: u32, ctx: &Context) -> Result<SyscallReturn> {
+    let offset = do_lseek(fd, offset, whence, ctx)?;
+    Ok(SyscallReturn::Return(offset as _))
+}
+
+/// The `llseek` system call of 32-bit programs, whose offset is split into two registers.
+pub fn compat_sys_llseek(
+    fd: FileDesc,
+    offset_high: u32,
+    offset_low: u32,
+    result_addr: Vaddr,
+    whence: u32,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let offset = ((offset_high as u64) << 32 | offset_low as u64) as isize;
+    let offset = do_lseek(fd, offset, whence, ctx)?;
+    ctx.user_space().write_val(result_addr, &(offset as i64))?;
+    Ok(SyscallReturn::Return(0))
+}
+
+fn do_lseek(fd: FileDesc, offset: isize, whence: u32, ctx: &Context) -> Result<usize> {
     debug!("fd = {}, offset = {}, whence = {}", fd, offset, whence);
 
     let seek_from = match whence {
@@ -26,6 +46,5 @@ pub fn sys_lseek(fd: FileDesc, offset: isize, whence: u32, ctx: &Context) -> Res
     let mut file_table = ctx.thread_local.file_table().borrow_mut();
     let file = get_file_fast!(&mut file_table, fd);
 
-    let offset = file.seek(seek_from)?;
-    Ok(SyscallReturn::Return(offset as _))
+    file.seek(seek_from)
 }
@@ -45,6 +45,8 @@ pub fn sys_madvise(
             warn!("MADV_DONTNEED isn't implemented, do nothing for now.");
         }
         MadviseBehavior::MADV_FREE => madv_free(start, end, ctx)?,
+        MadviseBehavior::MADV_MERGEABLE => madv_mergeable(start, end, true, ctx)?,
+        MadviseBehavior::MADV_UNMERGEABLE => madv_mergeable(start, end, false, ctx)?,
         _ => todo!(),
     }
     Ok(SyscallReturn::Return(0))
@@ -59,6 +61,12 @@ fn madv_free(start: Vaddr, end: Vaddr, ctx: &Context) -> Result<()> {
     Ok(())
 }
 
+fn madv_mergeable(start: Vaddr, end: Vaddr, is_mergeable: bool, ctx: &Context) -> Result<()> {
+    let user_space = ctx.user_space();
+    let root_vmar = user_space.root_vmar();
+    root_vmar.set_mergeable(start..end, is_mergeable)
+}
+
 #[repr(i32)]
 #[derive(Debug, Clone, Copy, TryFromInt)]
 #[expect(non_camel_case_types)]
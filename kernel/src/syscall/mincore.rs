@@ -0,0 +1,44 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use align_ext::AlignExt;
+
+use super::SyscallReturn;
+use crate::{prelude::*, vm::vmar::is_userspace_vaddr};
+
+pub fn sys_mincore(
+    start: Vaddr,
+    len: usize,
+    vec_addr: Vaddr,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    debug!(
+        "start = 0x{:x}, len = 0x{:x}, vec_addr = 0x{:x}",
+        start, len, vec_addr
+    );
+
+    if start % PAGE_SIZE != 0 {
+        return_errno_with_message!(Errno::EINVAL, "the start address should be page aligned");
+    }
+    if len == 0 {
+        return Ok(SyscallReturn::Return(0));
+    }
+    let end = start
+        .checked_add(len)
+        .filter(|end| *end <= usize::MAX - PAGE_SIZE)
+        .map(|end| end.align_up(PAGE_SIZE))
+        .filter(|end| is_userspace_vaddr(start) && is_userspace_vaddr(*end - 1))
+        .ok_or_else(|| Error::with_message(Errno::ENOMEM, "the range is not in the user space"))?;
+
+    let user_space = ctx.user_space();
+    // Like Linux, a page is reported as resident if it is mapped in the page table. The least
+    // significant bit of each byte is set for a resident page.
+    let residency: Vec<u8> = user_space
+        .root_vmar()
+        .page_residency(start..end)?
+        .into_iter()
+        .map(u8::from)
+        .collect();
+    user_space.write_bytes(vec_addr, &mut VmReader::from(residency.as_slice()))?;
+
+    Ok(SyscallReturn::Return(0))
+}
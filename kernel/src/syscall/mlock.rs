@@ -0,0 +1,94 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::ops::Range;
+
+use align_ext::AlignExt;
+
+use super::SyscallReturn;
+use crate::{prelude::*, vm::vmar::LockOption};
+
+pub fn sys_mlock(start: Vaddr, len: usize, ctx: &Context) -> Result<SyscallReturn> {
+    sys_mlock2(start, len, 0, ctx)
+}
+
+pub fn sys_mlock2(start: Vaddr, len: usize, flags: u32, ctx: &Context) -> Result<SyscallReturn> {
+    let flags = MlockFlags::from_bits(flags)
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid flags"))?;
+    debug!(
+        "start = 0x{:x}, len = 0x{:x}, flags = {:?}",
+        start, len, flags
+    );
+
+    let option = if flags.contains(MlockFlags::MLOCK_ONFAULT) {
+        LockOption::OnFault
+    } else {
+        LockOption::Populate
+    };
+    let range = lock_range(start, len)?;
+    if !range.is_empty() {
+        ctx.user_space().root_vmar().lock(range, option)?;
+    }
+    Ok(SyscallReturn::Return(0))
+}
+
+pub fn sys_munlock(start: Vaddr, len: usize, ctx: &Context) -> Result<SyscallReturn> {
+    debug!("start = 0x{:x}, len = 0x{:x}", start, len);
+
+    let range = lock_range(start, len)?;
+    if !range.is_empty() {
+        ctx.user_space().root_vmar().unlock(range)?;
+    }
+    Ok(SyscallReturn::Return(0))
+}
+
+pub fn sys_mlockall(flags: u32, ctx: &Context) -> Result<SyscallReturn> {
+    let flags = MlockallFlags::from_bits(flags)
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid flags"))?;
+    debug!("flags = {:?}", flags);
+
+    if !flags.intersects(MlockallFlags::MCL_CURRENT | MlockallFlags::MCL_FUTURE) {
+        return_errno_with_message!(Errno::EINVAL, "neither current nor future is locked");
+    }
+
+    let option = if flags.contains(MlockallFlags::MCL_ONFAULT) {
+        LockOption::OnFault
+    } else {
+        LockOption::Populate
+    };
+    let user_space = ctx.user_space();
+    let root_vmar = user_space.root_vmar();
+    root_vmar.set_lock_future(flags.contains(MlockallFlags::MCL_FUTURE).then_some(option));
+    if flags.contains(MlockallFlags::MCL_CURRENT) {
+        root_vmar.lock_all(option)?;
+    }
+    Ok(SyscallReturn::Return(0))
+}
+
+pub fn sys_munlockall(ctx: &Context) -> Result<SyscallReturn> {
+    ctx.user_space().root_vmar().unlock_all()?;
+    Ok(SyscallReturn::Return(0))
+}
+
+/// Returns the page-aligned range that covers `start..start + len`.
+fn lock_range(start: Vaddr, len: usize) -> Result<Range<Vaddr>> {
+    let aligned_start = start.align_down(PAGE_SIZE);
+    let end = start
+        .checked_add(len)
+        .filter(|end| *end <= usize::MAX - PAGE_SIZE)
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "the range is too large"))?;
+    Ok(aligned_start..end.align_up(PAGE_SIZE))
+}
+
+bitflags! {
+    struct MlockFlags: u32 {
+        const MLOCK_ONFAULT = 0x01;
+    }
+}
+
+bitflags! {
+    struct MlockallFlags: u32 {
+        const MCL_CURRENT = 0x01;
+        const MCL_FUTURE = 0x02;
+        const MCL_ONFAULT = 0x04;
+    }
+}
@@ -10,12 +10,17 @@ use crate::{
     fs::{
         file_handle::FileLike,
         file_table::{get_file_fast, FileDesc},
+        hugetlbfs::HugetlbInode,
+        path::PerMountFlags,
     },
+    perf::PerfEvent,
     prelude::*,
+    security::hooks,
     vm::{
+        hugetlb::{self, HugePageSize},
         perms::VmPerms,
-        vmar::is_userspace_vaddr,
-        vmo::{VmoOptions, VmoRightsOp},
+        vmar::{is_userspace_vaddr, LockOption},
+        vmo::{Vmo, VmoOptions, VmoRightsOp},
     },
 };
 
@@ -42,6 +47,32 @@ pub fn sys_mmap(
     Ok(SyscallReturn::Return(res as _))
 }
 
+/// The `mmap2` system call of 32-bit programs, whose offset is in the units of 4096 bytes.
+pub fn compat_sys_mmap2(
+    addr: u64,
+    len: u64,
+    perms: u64,
+    flags: u64,
+    fd: u64,
+    pgoff: u64,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    const MMAP2_UNIT: u64 = 4096;
+
+    let perms = VmPerms::from_bits_truncate(perms as u32);
+    let option = MMapOptions::try_from(flags as u32)?;
+    let res = do_sys_mmap(
+        addr as usize,
+        len as usize,
+        perms,
+        option,
+        fd as _,
+        (pgoff * MMAP2_UNIT) as usize,
+        ctx,
+    )?;
+    Ok(SyscallReturn::Return(res as _))
+}
+
 fn do_sys_mmap(
     addr: Vaddr,
     len: usize,
@@ -82,6 +113,21 @@ fn do_sys_mmap(
         return_errno_with_message!(Errno::ENOMEM, "mmap (addr + len) too large");
     }
 
+    // The mappings of huge pages are aligned to the huge pages.
+    let huge_page_size = huge_page_size(&option, fd, ctx)?;
+    let len = match huge_page_size {
+        Some(page_size) => {
+            if option.flags.contains(MMapFlags::MAP_FIXED) && addr % page_size.bytes() != 0 {
+                return_errno_with_message!(
+                    Errno::EINVAL,
+                    "the address is not aligned to huge pages"
+                );
+            }
+            len.align_up(page_size.bytes())
+        }
+        None => len,
+    };
+
     // On x86, `PROT_WRITE` implies `PROT_READ`.
     // <https://man7.org/linux/man-pages/man2/mmap.2.html>
     #[cfg(target_arch = "x86_64")]
@@ -107,6 +153,14 @@ fn do_sys_mmap(
             options = options.is_shared(true);
         }
 
+        if let Some(page_size) = huge_page_size {
+            options = options.align(page_size.bytes());
+        }
+
+        if flags.contains(MMapFlags::MAP_LOCKED) {
+            options = options.lock(LockOption::Populate);
+        }
+
         if option.flags.contains(MMapFlags::MAP_ANONYMOUS) {
             if offset != 0 {
                 return_errno_with_message!(
@@ -114,9 +168,17 @@ fn do_sys_mmap(
                     "offset must be zero for anonymous mapping"
                 );
             }
-
-            // Anonymous shared mapping should share the same memory pages.
-            if option.typ() == MMapType::Shared {
+            hooks::mmap_file(None, vm_perms)?;
+
+            if let Some(page_size) = huge_page_size {
+                let reserve = !option.flags.contains(MMapFlags::MAP_NORESERVE);
+                let (vmo, _) = hugetlb::alloc_vmo(page_size, len, reserve)?;
+                // TODO: Copy the huge pages of the private mappings on `fork`. For now, the
+                // private mappings are shared with the child processes like the shared ones,
+                // since a copy-on-write fault would replace the huge pages with base pages.
+                options = options.vmo(vmo.to_dyn()).is_shared(true);
+            } else if option.typ() == MMapType::Shared {
+                // Anonymous shared mapping should share the same memory pages.
                 let shared_vmo = {
                     let vmo_options: VmoOptions<Rights> = VmoOptions::new(len);
                     vmo_options.alloc()?
@@ -127,27 +189,63 @@ fn do_sys_mmap(
             let vmo = {
                 let mut file_table = ctx.thread_local.file_table().borrow_mut();
                 let file = get_file_fast!(&mut file_table, fd);
-                let inode_handle = file.as_inode_or_err()?;
 
-                let access_mode = inode_handle.access_mode();
-                if vm_perms.contains(VmPerms::READ) && !access_mode.is_readable() {
-                    return_errno!(Errno::EACCES);
-                }
-                if option.typ() == MMapType::Shared
-                    && vm_perms.contains(VmPerms::WRITE)
-                    && !access_mode.is_writable()
-                {
-                    return_errno!(Errno::EACCES);
+                // Some sockets (e.g., packet sockets) can share their buffers with the user space.
+                if let Some(socket) = file.as_socket() {
+                    socket.mmap_vmo()?
+                } else if let Some(perf_event) = file.downcast_ref::<PerfEvent>() {
+                    // The ring buffers of perf events are shared with the user space, which
+                    // consumes the records and writes back the positions.
+                    if offset != 0 || option.typ() != MMapType::Shared {
+                        return_errno_with_message!(
+                            Errno::EINVAL,
+                            "perf ring buffers must be mapped shared from the start"
+                        );
+                    }
+                    perf_event.mmap_vmo(len)?
+                } else if let Some(vmo) = kvm_run_vmo(&file, offset, option.typ(), len)? {
+                    vmo
+                } else {
+                    let inode_handle = file.as_inode_or_err()?;
+
+                    let access_mode = inode_handle.access_mode();
+                    if vm_perms.contains(VmPerms::READ) && !access_mode.is_readable() {
+                        return_errno!(Errno::EACCES);
+                    }
+                    if option.typ() == MMapType::Shared
+                        && vm_perms.contains(VmPerms::WRITE)
+                        && !access_mode.is_writable()
+                    {
+                        return_errno!(Errno::EACCES);
+                    }
+
+                    if vm_perms.contains(VmPerms::EXEC)
+                        && inode_handle
+                            .dentry()
+                            .mount_node()
+                            .flags()
+                            .contains(PerMountFlags::NOEXEC)
+                    {
+                        return_errno_with_message!(Errno::EPERM, "the mount disallows execution");
+                    }
+                    hooks::mmap_file(Some(inode_handle.dentry()), vm_perms)?;
+
+                    let inode = inode_handle.dentry().inode();
+                    if let Some(hugetlb_inode) = inode.downcast_ref::<HugetlbInode>() {
+                        hugetlb_inode.prepare_mmap(
+                            &(offset..offset + len),
+                            !option.flags.contains(MMapFlags::MAP_NORESERVE),
+                            vm_perms.contains(VmPerms::WRITE),
+                        )?;
+                    }
+                    inode
+                        .page_cache()
+                        .ok_or(Error::with_message(
+                            Errno::EBADF,
+                            "File does not have page cache",
+                        ))?
+                        .to_dyn()
                 }
-
-                let inode = inode_handle.dentry().inode();
-                inode
-                    .page_cache()
-                    .ok_or(Error::with_message(
-                        Errno::EBADF,
-                        "File does not have page cache",
-                    ))?
-                    .to_dyn()
             };
 
             options = options
@@ -163,6 +261,60 @@ fn do_sys_mmap(
     Ok(map_addr)
 }
 
+/// Returns the size of the huge pages if the mapping is backed by huge pages, i.e., it is an
+/// anonymous mapping with `MAP_HUGETLB` or a mapping of a file in hugetlbfs.
+fn huge_page_size(
+    option: &MMapOptions,
+    fd: FileDesc,
+    ctx: &Context,
+) -> Result<Option<HugePageSize>> {
+    let is_hugetlb = option.flags.contains(MMapFlags::MAP_HUGETLB);
+    if option.flags.contains(MMapFlags::MAP_ANONYMOUS) {
+        if !is_hugetlb {
+            return Ok(None);
+        }
+        return option.huge_page_size().map(Some);
+    }
+
+    let mut file_table = ctx.thread_local.file_table().borrow_mut();
+    let file = get_file_fast!(&mut file_table, fd);
+    let page_size = file.as_inode_or_err().ok().and_then(|inode_handle| {
+        let inode = inode_handle.dentry().inode();
+        inode
+            .downcast_ref::<HugetlbInode>()
+            .map(HugetlbInode::page_size)
+    });
+    if is_hugetlb && page_size.is_none() {
+        return_errno_with_message!(Errno::EINVAL, "`MAP_HUGETLB` requires an anonymous mapping");
+    }
+    Ok(page_size)
+}
+
+/// Returns the VMO of `struct kvm_run` if the file is a vCPU file of KVM.
+fn kvm_run_vmo(
+    file: &Arc<dyn FileLike>,
+    offset: usize,
+    typ: MMapType,
+    len: usize,
+) -> Result<Option<Vmo>> {
+    #[cfg(target_arch = "riscv64")]
+    if let Some(vcpu) = file.downcast_ref::<crate::kvm::Vcpu>() {
+        // `struct kvm_run` is shared with the user space, which handles the exits and writes
+        // back the results.
+        if offset != 0 || typ != MMapType::Shared {
+            return_errno_with_message!(
+                Errno::EINVAL,
+                "`kvm_run` must be mapped shared from the start"
+            );
+        }
+        return vcpu.mmap_vmo(len).map(Some);
+    }
+    #[cfg(not(target_arch = "riscv64"))]
+    let _ = (file, offset, typ, len);
+
+    Ok(None)
+}
+
 fn check_option(addr: Vaddr, option: &MMapOptions) -> Result<()> {
     if option.typ() == MMapType::File {
         return_errno_with_message!(Errno::EINVAL, "Invalid mmap type");
@@ -184,6 +336,11 @@ fn check_option(addr: Vaddr, option: &MMapOptions) -> Result<()> {
 // The map type mask
 const MAP_TYPE: u32 = 0xf;
 
+// The bits for the size of the huge pages (e.g., `MAP_HUGE_2MB`), which is the base-2
+// logarithm of the size
+const MAP_HUGE_SHIFT: u32 = 26;
+const MAP_HUGE_MASK: u32 = 0x3f;
+
 #[derive(Copy, Clone, PartialEq, Debug, TryFromInt)]
 #[repr(u8)]
 pub enum MMapType {
@@ -216,6 +373,8 @@ bitflags! {
 pub struct MMapOptions {
     typ: MMapType,
     flags: MMapFlags,
+    /// The base-2 logarithm of the size of the huge pages, or zero for the default size.
+    huge_page_shift: u32,
 }
 
 impl TryFrom<u32> for MMapOptions {
@@ -225,11 +384,17 @@ impl TryFrom<u32> for MMapOptions {
         let typ_raw = (value & MAP_TYPE) as u8;
         let typ = MMapType::try_from(typ_raw)?;
 
-        let flags_raw = value & !MAP_TYPE;
+        let huge_page_shift = (value >> MAP_HUGE_SHIFT) & MAP_HUGE_MASK;
+
+        let flags_raw = value & !MAP_TYPE & !(MAP_HUGE_MASK << MAP_HUGE_SHIFT);
         let Some(flags) = MMapFlags::from_bits(flags_raw) else {
             return Err(Error::with_message(Errno::EINVAL, "unknown mmap flags"));
         };
-        Ok(MMapOptions { typ, flags })
+        Ok(MMapOptions {
+            typ,
+            flags,
+            huge_page_shift,
+        })
     }
 }
 
@@ -241,4 +406,13 @@ impl MMapOptions {
     pub fn flags(&self) -> MMapFlags {
         self.flags
     }
+
+    /// Returns the size of the huge pages of a `MAP_HUGETLB` mapping.
+    pub fn huge_page_size(&self) -> Result<HugePageSize> {
+        if self.huge_page_shift == 0 {
+            return Ok(HugePageSize::DEFAULT);
+        }
+        HugePageSize::from_shift(self.huge_page_shift)
+            .ok_or_else(|| Error::with_message(Errno::EINVAL, "unsupported huge page size"))
+    }
 }
@@ -3,16 +3,25 @@
 //! Read the Cpu ctx content then dispatch syscall to corresponding handler
 //! The each sub module contains functions that handle real syscall logic.
 pub use clock_gettime::ClockId;
-use ostd::cpu::context::UserContext;
+use ostd::{cpu::context::UserContext, user::UserContextApi};
 
-use crate::{context::Context, cpu::LinuxAbi, prelude::*};
+use crate::{
+    context::Context,
+    cpu::LinuxAbi,
+    prelude::*,
+    security::seccomp,
+    trace::{SYS_ENTER, SYS_EXIT},
+};
 
 mod accept;
 mod access;
+mod add_key;
+mod adjtimex;
 mod alarm;
 mod arch;
 mod arch_prctl;
 mod bind;
+mod bpf;
 mod brk;
 mod capget;
 mod capset;
@@ -25,6 +34,7 @@ mod clone;
 mod close;
 mod connect;
 mod constants;
+mod copy_file_range;
 mod dup;
 mod epoll;
 mod eventfd;
@@ -35,6 +45,7 @@ mod fallocate;
 mod fcntl;
 mod flock;
 mod fork;
+mod fsopen;
 mod fsync;
 mod futex;
 mod get_priority;
@@ -62,38 +73,64 @@ mod gettimeofday;
 mod getuid;
 mod getxattr;
 mod ioctl;
+mod kcmp;
+#[cfg(target_arch = "riscv64")]
+mod kexec_file_load;
+#[cfg(target_arch = "riscv64")]
+mod kexec_load;
+mod keyctl;
 mod kill;
+mod landlock;
 mod link;
 mod listen;
 mod listxattr;
 mod lseek;
 mod madvise;
+mod mincore;
 mod mkdir;
 mod mknod;
+mod mlock;
 mod mmap;
 mod mount;
 mod mprotect;
+mod mq_getsetattr;
+mod mq_notify;
+mod mq_open;
+mod mq_timedreceive;
+mod mq_timedsend;
+mod mq_unlink;
+mod msgctl;
+mod msgget;
+mod msgrcv;
+mod msgsnd;
 mod msync;
 mod munmap;
 mod nanosleep;
 mod open;
 mod pause;
+mod perf_event_open;
 mod pipe;
 mod poll;
 mod prctl;
 mod pread64;
 mod preadv;
 mod prlimit64;
+mod process_vm;
 mod pselect6;
 mod pwrite64;
 mod pwritev;
 mod read;
 mod readlink;
+mod reboot;
 mod recvfrom;
 mod recvmsg;
 mod removexattr;
 mod rename;
+mod request_key;
+#[cfg(target_arch = "riscv64")]
+mod riscv_hwprobe;
 mod rmdir;
+mod rseq;
 mod rt_sigaction;
 mod rt_sigpending;
 mod rt_sigprocmask;
@@ -109,6 +146,7 @@ mod sched_setattr;
 mod sched_setparam;
 mod sched_setscheduler;
 mod sched_yield;
+mod seccomp;
 mod select;
 mod semctl;
 mod semget;
@@ -133,26 +171,35 @@ mod setsid;
 mod setsockopt;
 mod setuid;
 mod setxattr;
+mod shmat;
+mod shmctl;
+mod shmdt;
+mod shmget;
 mod shutdown;
 mod sigaltstack;
 mod signalfd;
 mod socket;
 mod socketpair;
+mod splice;
 mod stat;
 mod statfs;
+mod strace;
 mod symlink;
 mod sync;
 mod sysinfo;
+mod syslog;
 mod tgkill;
 mod time;
 mod timer_create;
 mod timer_settime;
+mod times;
 mod truncate;
 mod umask;
 mod umount;
 mod uname;
 mod unlink;
 mod utimens;
+mod vmsplice;
 mod wait4;
 mod waitid;
 mod write;
@@ -272,6 +319,12 @@ macro_rules! dispatch_fn_inner {
     };
 }
 
+macro_rules! nr_syscall_args {
+    ( ( args[ .. $cnt: tt ] $(, $($user_ctx: tt)*)? ) ) => {
+        $cnt
+    };
+}
+
 macro_rules! impl_syscall_nums_and_dispatch_fn {
     // $args, $user_ctx, and $dispatcher_name are needed since Rust macro is hygienic
     ( $( $name: ident = $num: literal => $handler: ident $args: tt );* $(;)? ) => {
@@ -300,12 +353,25 @@ macro_rules! impl_syscall_nums_and_dispatch_fn {
                 }
             }
         }
+
+        // Finally, define the function that describes the syscalls for tracing
+        /// Returns the name of the syscall constant (e.g., `SYS_READ`) and the number of the
+        /// arguments, or `None` if the syscall is unimplemented.
+        pub fn syscall_info(syscall_number: u64) -> Option<(&'static str, usize)> {
+            match syscall_number {
+                $(
+                    $num => Some((stringify!($name), $crate::syscall::nr_syscall_args!($args))),
+                )*
+                _ => None,
+            }
+        }
     }
 }
 
 // Export macros to sub-modules
 use dispatch_fn_inner;
 use impl_syscall_nums_and_dispatch_fn;
+use nr_syscall_args;
 use syscall_handler;
 
 pub struct SyscallArgument {
@@ -335,11 +401,69 @@ impl SyscallArgument {
 
 pub fn handle_syscall(ctx: &Context, user_ctx: &mut UserContext) {
     let syscall_frame = SyscallArgument::new_from_context(user_ctx);
-    let syscall_return = arch::syscall_dispatch(
+    // A 32-bit program makes the system calls of the 32-bit architecture.
+    let is_compat = user_ctx.is_compat();
+
+    // The system calls that are dispatched to the user space are not made or traced.
+    if ctx
+        .thread_local
+        .syscall_user_dispatch()
+        .check_syscall(ctx, user_ctx, is_compat)
+    {
+        return;
+    }
+
+    let [arg0, arg1, arg2, arg3, arg4, arg5] = syscall_frame.args;
+    SYS_ENTER.emit(&[
         syscall_frame.syscall_number,
-        syscall_frame.args,
+        arg0,
+        arg1,
+        arg2,
+        arg3,
+        arg4,
+        arg5,
+    ]);
+
+    if let Some(return_value) = seccomp::check_syscall(
         ctx,
-        user_ctx,
+        syscall_frame.syscall_number,
+        &syscall_frame.args,
+        user_ctx.instruction_pointer(),
+        is_compat,
+    ) {
+        user_ctx.set_syscall_ret(return_value as usize);
+        SYS_EXIT.emit(&[syscall_frame.syscall_number, user_ctx.syscall_ret() as u64]);
+        strace::trace_syscall(
+            ctx,
+            syscall_frame.syscall_number,
+            &syscall_frame.args,
+            is_compat,
+            Ok(SyscallReturn::Return(return_value)),
+        );
+        return;
+    }
+
+    let syscall_return = match is_compat {
+        #[cfg(target_arch = "riscv64")]
+        true => arch::compat::syscall_dispatch(
+            syscall_frame.syscall_number,
+            syscall_frame.args,
+            ctx,
+            user_ctx,
+        ),
+        _ => arch::syscall_dispatch(
+            syscall_frame.syscall_number,
+            syscall_frame.args,
+            ctx,
+            user_ctx,
+        ),
+    };
+    strace::trace_syscall(
+        ctx,
+        syscall_frame.syscall_number,
+        &syscall_frame.args,
+        is_compat,
+        syscall_return,
     );
 
     match syscall_return {
@@ -354,6 +478,7 @@ pub fn handle_syscall(ctx: &Context, user_ctx: &mut UserContext) {
             user_ctx.set_syscall_ret((-errno) as usize)
         }
     }
+    SYS_EXIT.emit(&[syscall_frame.syscall_number, user_ctx.syscall_ret() as u64]);
 }
 
 #[macro_export]
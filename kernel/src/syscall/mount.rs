@@ -1,22 +1,39 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use super::SyscallReturn;
+use aster_block::BlockDevice;
+
+use super::{fsopen::FsContext, SyscallReturn};
+#[cfg(target_arch = "riscv64")]
+use crate::fs::pstore::PstoreFs;
 use crate::{
     fs::{
+        cgroupfs,
         exfat::{ExfatFS, ExfatMountOptions},
         ext2::Ext2,
+        file_table::{get_file_fast, FdFlags, FileDesc},
+        flashfs::FlashFs,
         fs_resolver::{FsPath, AT_FDCWD},
-        path::Dentry,
-        utils::{FileSystem, InodeType},
+        hugetlbfs::{HugetlbFs, HugetlbfsMountOptions},
+        inode_handle::InodeHandle,
+        mqueue::MqueueFs,
+        nfs::NfsFs,
+        overlayfs::{OverlayFs, OverlayMountOptions},
+        path::{Dentry, MountNode, PerMountFlags},
+        ramfs::{RamFS, TmpfsMountOptions},
+        rootfs::root_mount,
+        tracefs,
+        utils::{AccessMode, FileSystem, Inode, InodeType, StatusFlags},
+        vfat::{VfatFs, VfatMountOptions},
     },
     prelude::*,
+    security::hooks,
     syscall::constants::MAX_FILENAME_LEN,
 };
 
 /// The `data` argument is interpreted by the different filesystems.
 /// Typically it is a string of comma-separated options understood by
-/// this filesystem. The current implementation only considers the case
-/// where it is `NULL`. Because it should be interpreted by the specific filesystems.
+/// this filesystem. The current implementation only passes it to the
+/// overlay filesystem and tmpfs, and ignores it for the others.
 pub fn sys_mount(
     devname_addr: Vaddr,
     dirname_addr: Vaddr,
@@ -42,11 +59,12 @@ pub fn sys_mount(
         let fs_path = FsPath::new(AT_FDCWD, dirname.as_ref())?;
         ctx.posix_thread.fs().resolver().read().lookup(&fs_path)?
     };
+    hooks::sb_mount(&devname.to_string_lossy(), &dst_dentry, flags as u32)?;
 
     if mount_flags.contains(MountFlags::MS_REMOUNT) && mount_flags.contains(MountFlags::MS_BIND) {
-        do_reconfigure_mnt()?;
+        do_reconfigure_mnt(dst_dentry, mount_flags)?;
     } else if mount_flags.contains(MountFlags::MS_REMOUNT) {
-        do_remount()?;
+        do_remount(dst_dentry, mount_flags)?;
     } else if mount_flags.contains(MountFlags::MS_BIND) {
         do_bind_mount(
             devname,
@@ -63,18 +81,162 @@ pub fn sys_mount(
     } else if mount_flags.contains(MountFlags::MS_MOVE) {
         do_move_mount_old(devname, dst_dentry, ctx)?;
     } else {
-        do_new_mount(devname, fstype_addr, dst_dentry, ctx)?;
+        let data = if data == 0 {
+            None
+        } else {
+            Some(user_space.read_cstring(data, PAGE_SIZE)?)
+        };
+        do_new_mount(devname, fstype_addr, data, dst_dentry, mount_flags, ctx)?;
     }
 
     Ok(SyscallReturn::Return(0))
 }
 
-fn do_reconfigure_mnt() -> Result<()> {
-    return_errno_with_message!(Errno::EINVAL, "do_reconfigure_mnt is not supported");
+/// Creates a detached mount of the file system that is created by `fsconfig()`.
+///
+/// The returned file descriptor refers to the root of the mount, which can be
+/// attached to the mount tree by `move_mount()`.
+pub fn sys_fsmount(
+    fs_fd: FileDesc,
+    flags: u32,
+    attr_flags: u32,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let flags = FsMountFlags::from_bits(flags)
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "unknown flags"))?;
+    // The access times are not updated, so `MOUNT_ATTR_STRICTATIME` is simply accepted.
+    let per_mount_flags = PerMountFlags::from_bits(attr_flags & !MOUNT_ATTR_STRICTATIME)
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "unsupported mount attributes"))?;
+    debug!(
+        "fs_fd = {}, flags = {:?}, attr_flags = {:?}",
+        fs_fd, flags, per_mount_flags
+    );
+
+    let fs = {
+        let mut file_table = ctx.thread_local.file_table().borrow_mut();
+        let file = get_file_fast!(&mut file_table, fs_fd);
+        let fs_context = file
+            .downcast_ref::<FsContext>()
+            .ok_or_else(|| Error::with_message(Errno::EINVAL, "not a file system context"))?;
+        fs_context.take_fs()?
+    };
+
+    let mount = MountNode::new_root(fs);
+    mount.set_flags(per_mount_flags);
+    let inode_handle = InodeHandle::new_unchecked_access(
+        Dentry::new_fs_root(mount),
+        AccessMode::O_RDONLY,
+        StatusFlags::O_PATH,
+    )?;
+
+    let fd = {
+        let file_table = ctx.thread_local.file_table().borrow();
+        let mut file_table_locked = file_table.write();
+        let fd_flags = if flags.contains(FsMountFlags::FSMOUNT_CLOEXEC) {
+            FdFlags::CLOEXEC
+        } else {
+            FdFlags::empty()
+        };
+        file_table_locked.insert(Arc::new(inode_handle), fd_flags)?
+    };
+
+    Ok(SyscallReturn::Return(fd as _))
 }
 
-fn do_remount() -> Result<()> {
-    return_errno_with_message!(Errno::EINVAL, "do_remount is not supported");
+/// Moves a mount, which may be a detached one created by `fsmount()`, to the
+/// location of `to_path`.
+pub fn sys_move_mount(
+    from_dirfd: FileDesc,
+    from_path_addr: Vaddr,
+    to_dirfd: FileDesc,
+    to_path_addr: Vaddr,
+    flags: u32,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let user_space = ctx.user_space();
+    let from_path = user_space.read_cstring(from_path_addr, MAX_FILENAME_LEN)?;
+    let to_path = user_space.read_cstring(to_path_addr, MAX_FILENAME_LEN)?;
+    let flags = MoveMountFlags::from_bits(flags)
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "unknown flags"))?;
+    debug!(
+        "from_dirfd = {}, from_path = {:?}, to_dirfd = {}, to_path = {:?}, flags = {:?}",
+        from_dirfd, from_path, to_dirfd, to_path, flags
+    );
+
+    let src_dentry = lookup_mount_path(
+        from_dirfd,
+        &from_path.to_string_lossy(),
+        flags.contains(MoveMountFlags::MOVE_MOUNT_F_EMPTY_PATH),
+        flags.contains(MoveMountFlags::MOVE_MOUNT_F_SYMLINKS),
+        ctx,
+    )?;
+    let dst_dentry = lookup_mount_path(
+        to_dirfd,
+        &to_path.to_string_lossy(),
+        flags.contains(MoveMountFlags::MOVE_MOUNT_T_EMPTY_PATH),
+        flags.contains(MoveMountFlags::MOVE_MOUNT_T_SYMLINKS),
+        ctx,
+    )?;
+
+    if !src_dentry.is_root_of_mount() {
+        return_errno_with_message!(Errno::EINVAL, "the source is not a mount");
+    }
+    if Arc::ptr_eq(src_dentry.mount_node(), root_mount()) {
+        return_errno_with_message!(Errno::EINVAL, "the root mount can not be moved");
+    }
+
+    src_dentry.mount_node().graft_mount_node_tree(&dst_dentry)?;
+
+    Ok(SyscallReturn::Return(0))
+}
+
+/// Looks up the path of `move_mount()`.
+///
+/// An empty path refers to the file of `dirfd` if `empty_path` is true.
+fn lookup_mount_path(
+    dirfd: FileDesc,
+    path: &str,
+    empty_path: bool,
+    follow_symlinks: bool,
+    ctx: &Context,
+) -> Result<Dentry> {
+    if path.is_empty() {
+        if !empty_path {
+            return_errno_with_message!(Errno::ENOENT, "path is empty");
+        }
+        let mut file_table = ctx.thread_local.file_table().borrow_mut();
+        let file = get_file_fast!(&mut file_table, dirfd);
+        return Ok(file.as_inode_or_err()?.dentry().clone());
+    }
+
+    let fs_path = FsPath::new(dirfd, path)?;
+    let fs = ctx.posix_thread.fs().resolver().read();
+    if follow_symlinks {
+        fs.lookup(&fs_path)
+    } else {
+        fs.lookup_no_follow(&fs_path)
+    }
+}
+
+/// Changes the flags of a mount without touching the file system.
+///
+/// Such as use user command `mount -o remount,bind,ro dst`.
+fn do_reconfigure_mnt(target_dentry: Dentry, flags: MountFlags) -> Result<()> {
+    if !target_dentry.is_root_of_mount() {
+        return_errno_with_message!(Errno::EINVAL, "the target is not a mount");
+    }
+    target_dentry
+        .mount_node()
+        .set_flags(flags.per_mount_flags());
+    Ok(())
+}
+
+/// Changes the flags of a mount and reconfigures its file system.
+///
+/// The file systems cannot be reconfigured yet, so only the flags of the mount are
+/// changed, which are sufficient to make a mount read-only or read-write.
+fn do_remount(target_dentry: Dentry, flags: MountFlags) -> Result<()> {
+    do_reconfigure_mnt(target_dentry, flags)
 }
 
 /// Bind a mount to a dst location.
@@ -135,7 +297,9 @@ fn do_move_mount_old(src_name: CString, dst_dentry: Dentry, ctx: &Context) -> Re
 fn do_new_mount(
     devname: CString,
     fs_type: Vaddr,
+    data: Option<CString>,
     target_dentry: Dentry,
+    flags: MountFlags,
     ctx: &Context,
 ) -> Result<()> {
     if target_dentry.type_() != InodeType::Dir {
@@ -146,21 +310,64 @@ fn do_new_mount(
     if fs_type.is_empty() {
         return_errno_with_message!(Errno::EINVAL, "fs_type is empty");
     }
-    let fs = get_fs(fs_type, devname)?;
-    target_dentry.mount(fs)?;
+    let fs = get_fs(fs_type, devname, data, ctx)?;
+    let mount = target_dentry.mount(fs)?;
+    mount.set_flags(flags.per_mount_flags());
     Ok(())
 }
 
 /// Get the filesystem by fs_type and devname.
-fn get_fs(fs_type: CString, devname: CString) -> Result<Arc<dyn FileSystem>> {
+pub(super) fn get_fs(
+    fs_type: CString,
+    devname: CString,
+    data: Option<CString>,
+    ctx: &Context,
+) -> Result<Arc<dyn FileSystem>> {
+    // The filesystems that are not backed by devices.
+    let data = data.unwrap_or_default();
+    match fs_type.to_bytes() {
+        b"overlay" => {
+            let options = OverlayMountOptions::parse(&data.to_string_lossy())?;
+            return new_overlay_fs(&options, ctx);
+        }
+        b"tmpfs" => {
+            let options = TmpfsMountOptions::parse(&data.to_string_lossy())?;
+            return Ok(RamFS::new_tmpfs(options));
+        }
+        b"hugetlbfs" => {
+            let options = HugetlbfsMountOptions::parse(&data.to_string_lossy())?;
+            return Ok(HugetlbFs::new(options));
+        }
+        // The source of NFS is `<server-ip>:<path>`.
+        b"nfs" => {
+            let fs = NfsFs::mount(&devname.to_string_lossy(), &data.to_string_lossy())?;
+            return Ok(fs);
+        }
+        b"mqueue" => return Ok(MqueueFs::singleton().clone()),
+        b"tracefs" => return Ok(tracefs::singleton().clone()),
+        b"cgroup2" => return Ok(cgroupfs::singleton().clone()),
+        #[cfg(target_arch = "riscv64")]
+        b"pstore" => return Ok(PstoreFs::singleton().clone()),
+        _ => {}
+    }
+
     let devname = devname.to_str().unwrap();
-    let device = match aster_block::get_device(devname) {
+    // The flash filesystem is backed by an MTD device instead of a block device.
+    if fs_type.to_bytes() == b"flashfs" {
+        let Some(device) = lookup_mtd_device(devname) else {
+            return_errno_with_message!(Errno::ENOENT, "MTD device does not exist");
+        };
+        return Ok(FlashFs::open(device)?);
+    }
+
+    let device = match lookup_block_device(devname) {
         Some(device) => device,
         None => return_errno_with_message!(Errno::ENOENT, "Device does not exist"),
     };
     let fs_type = fs_type.to_str().unwrap();
     match fs_type {
-        "ext2" => {
+        // The Ext3 and Ext4 images are handled by the Ext2 driver as well.
+        "ext2" | "ext3" | "ext4" => {
             let ext2_fs = Ext2::open(device)?;
             Ok(ext2_fs)
         }
@@ -168,10 +375,75 @@ fn get_fs(fs_type: CString, devname: CString) -> Result<Arc<dyn FileSystem>> {
             let exfat_fs = ExfatFS::open(device, ExfatMountOptions::default())?;
             Ok(exfat_fs)
         }
+        "vfat" => {
+            let vfat_fs = VfatFs::open(device, VfatMountOptions::default())?;
+            Ok(vfat_fs)
+        }
         _ => return_errno_with_message!(Errno::EINVAL, "Invalid fs type"),
     }
 }
 
+fn new_overlay_fs(options: &OverlayMountOptions, ctx: &Context) -> Result<Arc<dyn FileSystem>> {
+    let lookup_layer = |path: &str| -> Result<Arc<dyn Inode>> {
+        let fs_path = FsPath::new(AT_FDCWD, path)?;
+        let dentry = ctx.posix_thread.fs().resolver().read().lookup(&fs_path)?;
+        Ok(dentry.inode().clone())
+    };
+
+    let lowers = options
+        .lowerdir
+        .iter()
+        .map(|path| lookup_layer(path))
+        .collect::<Result<Vec<_>>>()?;
+    let upper = options.upperdir.as_deref().map(lookup_layer).transpose()?;
+    let work = options.workdir.as_deref().map(lookup_layer).transpose()?;
+    let overlay_fs = OverlayFs::new(lowers, upper, work)?;
+    Ok(overlay_fs)
+}
+
+/// Looks up the block device by its name (e.g., `vda`) or its path (e.g., `/dev/loop0`
+/// or `/dev/mapper/root`).
+fn lookup_block_device(devname: &str) -> Option<Arc<dyn BlockDevice>> {
+    if let Some(name) = devname.strip_prefix("/dev/mapper/") {
+        let mapped_device = aster_dm::get_device(name)?;
+        return aster_block::get_device(&mapped_device.block_device_name());
+    }
+    aster_block::get_device(devname.strip_prefix("/dev/").unwrap_or(devname))
+}
+
+/// Looks up an MTD device by `mtdN`, `/dev/mtdN`, or `mtd:<name>` like JFFS2 of Linux.
+fn lookup_mtd_device(devname: &str) -> Option<Arc<aster_mtd::MtdDevice>> {
+    if let Some(name) = devname.strip_prefix("mtd:") {
+        return aster_mtd::find_device(name);
+    }
+    let index = devname
+        .strip_prefix("/dev/")
+        .unwrap_or(devname)
+        .strip_prefix("mtd")?
+        .parse()
+        .ok()?;
+    aster_mtd::get_device(index)
+}
+
+const MOUNT_ATTR_STRICTATIME: u32 = 0x20;
+
+bitflags! {
+    struct FsMountFlags: u32 {
+        const FSMOUNT_CLOEXEC = 1 << 0;
+    }
+}
+
+bitflags! {
+    struct MoveMountFlags: u32 {
+        const MOVE_MOUNT_F_SYMLINKS   = 1 << 0;
+        const MOVE_MOUNT_F_AUTOMOUNTS = 1 << 1;
+        const MOVE_MOUNT_F_EMPTY_PATH = 1 << 2;
+        const MOVE_MOUNT_T_SYMLINKS   = 1 << 4;
+        const MOVE_MOUNT_T_AUTOMOUNTS = 1 << 5;
+        const MOVE_MOUNT_T_EMPTY_PATH = 1 << 6;
+    }
+}
+
 bitflags! {
     struct MountFlags: u32 {
         const MS_RDONLY        =   1 << 0;       // Mount read-only.
@@ -198,3 +470,25 @@ bitflags! {
         const MS_KERNMOUNT     =   1 << 22;      // This is a kern_mount call.
     }
 }
+
+impl MountFlags {
+    /// Returns the flags that are specific to a mount.
+    fn per_mount_flags(&self) -> PerMountFlags {
+        let mut flags = PerMountFlags::empty();
+        let mapping = [
+            (MountFlags::MS_RDONLY, PerMountFlags::RDONLY),
+            (MountFlags::MS_NOSUID, PerMountFlags::NOSUID),
+            (MountFlags::MS_NODEV, PerMountFlags::NODEV),
+            (MountFlags::MS_NOEXEC, PerMountFlags::NOEXEC),
+            (MountFlags::MS_NOATIME, PerMountFlags::NOATIME),
+            (MountFlags::MS_NODIRATIME, PerMountFlags::NODIRATIME),
+            (MountFlags::MS_NOSYMFOLLOW, PerMountFlags::NOSYMFOLLOW),
+        ];
+        for (mount_flag, per_mount_flag) in mapping {
+            if self.contains(mount_flag) {
+                flags |= per_mount_flag;
+            }
+        }
+        flags
+    }
+}
@@ -0,0 +1,60 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::SyscallReturn;
+use crate::{
+    fs::{
+        file_table::{get_file_fast, FileDesc},
+        mqueue::MqueueInode,
+        utils::StatusFlags,
+    },
+    ipc::mqueue::MqAttr,
+    prelude::*,
+};
+
+pub fn sys_mq_getsetattr(
+    mqdes: FileDesc,
+    new_attr_addr: Vaddr,
+    old_attr_addr: Vaddr,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    debug!(
+        "[sys_mq_getsetattr] mqdes = {}, new_attr_addr = {:#x}, old_attr_addr = {:#x}",
+        mqdes, new_attr_addr, old_attr_addr
+    );
+
+    let user_space = ctx.user_space();
+    let new_flags = if new_attr_addr == 0 {
+        None
+    } else {
+        // Only `mq_flags` can be changed. The other fields are ignored.
+        let new_attr = user_space.read_val::<MqAttr>(new_attr_addr)?;
+        let flags = u32::try_from(new_attr.mq_flags)
+            .ok()
+            .and_then(StatusFlags::from_bits)
+            .filter(|flags| (*flags - StatusFlags::O_NONBLOCK).is_empty())
+            .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid message queue flags"))?;
+        Some(flags)
+    };
+
+    let mut file_table = ctx.thread_local.file_table().borrow_mut();
+    let file = get_file_fast!(&mut file_table, mqdes);
+    let queue = MqueueInode::from_file(&**file)?.queue();
+
+    let status_flags = file.status_flags();
+    if old_attr_addr != 0 {
+        let old_attr = MqAttr {
+            mq_flags: (status_flags & StatusFlags::O_NONBLOCK).bits() as i64,
+            mq_maxmsg: queue.maxmsg() as i64,
+            mq_msgsize: queue.msgsize() as i64,
+            mq_curmsgs: queue.curmsgs() as i64,
+            _reserved: [0; 4],
+        };
+        user_space.write_val(old_attr_addr, &old_attr)?;
+    }
+
+    if let Some(new_flags) = new_flags {
+        file.set_status_flags((status_flags - StatusFlags::O_NONBLOCK) | new_flags)?;
+    }
+
+    Ok(SyscallReturn::Return(0))
+}
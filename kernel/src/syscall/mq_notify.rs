@@ -0,0 +1,85 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::SyscallReturn;
+use crate::{
+    fs::{
+        file_table::{get_file_fast, FileDesc},
+        mqueue::MqueueInode,
+    },
+    ipc::mqueue::{MqNotify, NOTIFY_COOKIE_LEN},
+    net::socket::netlink::NetlinkNotifier,
+    prelude::*,
+    process::signal::{
+        c_types::{sigevent_t, SigNotify},
+        sig_num::SigNum,
+    },
+};
+
+pub fn sys_mq_notify(mqdes: FileDesc, sevp_addr: Vaddr, ctx: &Context) -> Result<SyscallReturn> {
+    debug!(
+        "[sys_mq_notify] mqdes = {}, sevp_addr = {:#x}",
+        mqdes, sevp_addr
+    );
+
+    let sig_event = if sevp_addr == 0 {
+        None
+    } else {
+        Some(ctx.user_space().read_val::<sigevent_t>(sevp_addr)?)
+    };
+    let notify = match &sig_event {
+        None => None,
+        Some(sig_event) => Some(parse_notify(sig_event, ctx)?),
+    };
+
+    let mut file_table = ctx.thread_local.file_table().borrow_mut();
+    let file = get_file_fast!(&mut file_table, mqdes);
+    let queue = MqueueInode::from_file(&**file)?.queue();
+
+    match notify {
+        // Remove the notification if it is registered by the current process.
+        None => queue.unregister_notify(ctx.process.pid()),
+        Some(notify) => {
+            queue.register_notify(ctx.process.pid(), ctx.posix_thread.weak_process(), notify)?
+        }
+    }
+
+    Ok(SyscallReturn::Return(0))
+}
+
+fn parse_notify(sig_event: &sigevent_t, ctx: &Context) -> Result<MqNotify> {
+    let notify = match SigNotify::try_from(sig_event.sigev_notify)? {
+        SigNotify::SIGEV_NONE => MqNotify::None,
+        SigNotify::SIGEV_SIGNAL => {
+            // Like Linux, the signal number zero is valid.
+            let sig_num = match sig_event.sigev_signo {
+                0 => None,
+                signo => Some(
+                    u8::try_from(signo)
+                        .ok()
+                        .and_then(|signo| SigNum::try_from(signo).ok())
+                        .ok_or_else(|| {
+                            Error::with_message(Errno::EINVAL, "invalid signal number")
+                        })?,
+                ),
+            };
+            MqNotify::Signal(sig_num, sig_event.sigev_value)
+        }
+        // The C library implements `SIGEV_THREAD` by passing a netlink socket, whose file
+        // descriptor is stored in `sigev_signo`, and a cookie, which is pointed by `sigev_value`.
+        SigNotify::SIGEV_THREAD => {
+            let mut cookie = [0u8; NOTIFY_COOKIE_LEN];
+            ctx.user_space().read_bytes(
+                sig_event.sigev_value.read_ptr(),
+                &mut VmWriter::from(cookie.as_mut_slice()),
+            )?;
+
+            let mut file_table = ctx.thread_local.file_table().borrow_mut();
+            let file = get_file_fast!(&mut file_table, sig_event.sigev_signo as FileDesc);
+            MqNotify::Netlink(NetlinkNotifier::new(&**file)?, cookie)
+        }
+        SigNotify::SIGEV_THREAD_ID => {
+            return_errno_with_message!(Errno::EINVAL, "invalid notification method");
+        }
+    };
+    Ok(notify)
+}
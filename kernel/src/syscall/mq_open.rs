@@ -0,0 +1,116 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::SyscallReturn;
+use crate::{
+    fs::{
+        file_table::FdFlags,
+        inode_handle::InodeHandle,
+        mqueue::MqueueFs,
+        utils::{AccessMode, CreationFlags, InodeMode, StatusFlags},
+    },
+    ipc::mqueue::{MessageQueue, MqAttr, QUEUES_MAX},
+    prelude::*,
+    process::credentials::capabilities::CapSet,
+    syscall::constants::MAX_FILENAME_LEN,
+};
+
+pub fn sys_mq_open(
+    name_addr: Vaddr,
+    oflag: u32,
+    mode: u16,
+    attr_addr: Vaddr,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let name = read_mq_name(name_addr, ctx)?;
+    let creation_flags = CreationFlags::from_bits_truncate(oflag);
+    debug!(
+        "[sys_mq_open] name = {:?}, oflag = {:#o}, mode = {:#o}, attr_addr = {:#x}",
+        name, oflag, mode, attr_addr
+    );
+
+    let access_mode = AccessMode::from_u32(oflag)?;
+    let status_flags = StatusFlags::from_bits_truncate(oflag) & StatusFlags::O_NONBLOCK;
+
+    let root = MqueueFs::root_dentry();
+    let inode_handle = match root.lookup(&name) {
+        Ok(dentry) => {
+            if creation_flags.contains(CreationFlags::O_CREAT | CreationFlags::O_EXCL) {
+                return_errno_with_message!(Errno::EEXIST, "the message queue exists");
+            }
+            InodeHandle::new(dentry, access_mode, status_flags)?
+        }
+        Err(err) if err.error() == Errno::ENOENT => {
+            if !creation_flags.contains(CreationFlags::O_CREAT) {
+                return_errno_with_message!(Errno::ENOENT, "the message queue does not exist");
+            }
+            create_queue(&name, mode, attr_addr, ctx)?;
+            let dentry = root.lookup(&name)?;
+            // Like `open`, the new queue can be opened regardless of its mode.
+            InodeHandle::new_unchecked_access(dentry, access_mode, status_flags)?
+        }
+        Err(err) => return Err(err),
+    };
+
+    let fd_flags = if creation_flags.contains(CreationFlags::O_CLOEXEC) {
+        FdFlags::CLOEXEC
+    } else {
+        FdFlags::empty()
+    };
+    let file_table = ctx.thread_local.file_table().borrow();
+    let fd = file_table
+        .write()
+        .insert(Arc::new(inode_handle), fd_flags)?;
+
+    Ok(SyscallReturn::Return(fd as _))
+}
+
+/// Reads the name of a message queue.
+///
+/// The C library strips the leading slash of the name, so the name passed to the kernel
+/// cannot contain any slashes. The length of the name is checked when the queue is looked up.
+pub(super) fn read_mq_name(name_addr: Vaddr, ctx: &Context) -> Result<String> {
+    let name = ctx
+        .user_space()
+        .read_cstring(name_addr, MAX_FILENAME_LEN)?
+        .to_string_lossy()
+        .into_owned();
+
+    if name.is_empty() {
+        return_errno_with_message!(Errno::ENOENT, "the name is empty");
+    }
+    if name.contains('/') || name == "." || name == ".." {
+        return_errno_with_message!(Errno::EACCES, "the name is invalid");
+    }
+    Ok(name)
+}
+
+fn create_queue(name: &str, mode: u16, attr_addr: Vaddr, ctx: &Context) -> Result<()> {
+    let credentials = ctx.posix_thread.credentials();
+    let is_privileged = credentials
+        .effective_capset()
+        .contains(CapSet::SYS_RESOURCE);
+
+    let fs = MqueueFs::singleton();
+    if fs.nr_queues() >= QUEUES_MAX && !is_privileged {
+        return_errno_with_message!(Errno::ENOSPC, "too many message queues");
+    }
+
+    let queue = if attr_addr == 0 {
+        MessageQueue::new_default()
+    } else {
+        let attr = ctx.user_space().read_val::<MqAttr>(attr_addr)?;
+        let (maxmsg, msgsize) = MessageQueue::check_attr(&attr, is_privileged)?;
+        MessageQueue::new(maxmsg, msgsize)
+    };
+
+    // TODO: Account the memory of the queue in `RLIMIT_MSGQUEUE`.
+    let mask_mode = mode & !ctx.posix_thread.fs().umask().read().get() & 0o777;
+    fs.create_queue(
+        name,
+        queue,
+        InodeMode::from_bits_truncate(mask_mode),
+        credentials.fsuid(),
+        credentials.fsgid(),
+    )?;
+    Ok(())
+}
@@ -0,0 +1,57 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{mq_timedsend::read_mq_timeout, SyscallReturn};
+use crate::{
+    fs::{
+        file_table::{get_file_fast, FileDesc},
+        mqueue::MqueueInode,
+        utils::StatusFlags,
+    },
+    prelude::*,
+};
+
+pub fn sys_mq_timedreceive(
+    mqdes: FileDesc,
+    msg_ptr: Vaddr,
+    msg_len: usize,
+    msg_prio_addr: Vaddr,
+    abs_timeout_addr: Vaddr,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    debug!(
+        "[sys_mq_timedreceive] mqdes = {}, msg_ptr = {:#x}, msg_len = {}, msg_prio_addr = {:#x}, abs_timeout_addr = {:#x}",
+        mqdes, msg_ptr, msg_len, msg_prio_addr, abs_timeout_addr
+    );
+
+    let timeout = read_mq_timeout(abs_timeout_addr, ctx)?;
+
+    let mut file_table = ctx.thread_local.file_table().borrow_mut();
+    let file = get_file_fast!(&mut file_table, mqdes);
+    let inode = MqueueInode::from_file(&**file)?;
+    if !file.access_mode().is_readable() {
+        return_errno_with_message!(Errno::EBADF, "the message queue is not opened for reading");
+    }
+
+    let queue = inode.queue();
+    if msg_len < queue.msgsize() {
+        return_errno_with_message!(Errno::EMSGSIZE, "the buffer is too small");
+    }
+
+    let is_nonblocking = file.status_flags().contains(StatusFlags::O_NONBLOCK);
+    let (data, priority) =
+        queue
+            .recv(is_nonblocking, timeout)
+            .map_err(|err| match err.error() {
+                Errno::EINTR => Error::new(Errno::ERESTARTSYS),
+                _ => err,
+            })?;
+
+    // FIXME: The message is lost if the user buffer is invalid. Linux has the same behavior.
+    let user_space = ctx.user_space();
+    user_space.write_bytes(msg_ptr, &mut VmReader::from(data.as_ref()))?;
+    if msg_prio_addr != 0 {
+        user_space.write_val(msg_prio_addr, &priority)?;
+    }
+
+    Ok(SyscallReturn::Return(data.len() as _))
+}
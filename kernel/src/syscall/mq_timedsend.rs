@@ -0,0 +1,78 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::time::Duration;
+
+use super::SyscallReturn;
+use crate::{
+    fs::{
+        file_table::{get_file_fast, FileDesc},
+        mqueue::MqueueInode,
+        utils::StatusFlags,
+    },
+    ipc::mqueue::MQ_PRIO_MAX,
+    prelude::*,
+    time::{clocks::RealTimeClock, timer::Timeout, timespec_t, wait::ManagedTimeout},
+};
+
+pub fn sys_mq_timedsend(
+    mqdes: FileDesc,
+    msg_ptr: Vaddr,
+    msg_len: usize,
+    msg_prio: u32,
+    abs_timeout_addr: Vaddr,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    debug!(
+        "[sys_mq_timedsend] mqdes = {}, msg_ptr = {:#x}, msg_len = {}, msg_prio = {}, abs_timeout_addr = {:#x}",
+        mqdes, msg_ptr, msg_len, msg_prio, abs_timeout_addr
+    );
+
+    if msg_prio >= MQ_PRIO_MAX {
+        return_errno_with_message!(Errno::EINVAL, "the priority is too large");
+    }
+    let timeout = read_mq_timeout(abs_timeout_addr, ctx)?;
+
+    let mut file_table = ctx.thread_local.file_table().borrow_mut();
+    let file = get_file_fast!(&mut file_table, mqdes);
+    let inode = MqueueInode::from_file(&**file)?;
+    if !file.access_mode().is_writable() {
+        return_errno_with_message!(Errno::EBADF, "the message queue is not opened for writing");
+    }
+
+    let queue = inode.queue();
+    if msg_len > queue.msgsize() {
+        return_errno_with_message!(Errno::EMSGSIZE, "the message is too long");
+    }
+    let mut data = vec![0u8; msg_len].into_boxed_slice();
+    ctx.user_space()
+        .read_bytes(msg_ptr, &mut VmWriter::from(data.as_mut()))?;
+
+    let is_nonblocking = file.status_flags().contains(StatusFlags::O_NONBLOCK);
+    let sender = (ctx.process.pid(), ctx.posix_thread.credentials().ruid());
+    queue
+        .send(data, msg_prio, is_nonblocking, timeout, sender)
+        .map_err(|err| match err.error() {
+            Errno::EINTR => Error::new(Errno::ERESTARTSYS),
+            _ => err,
+        })?;
+
+    Ok(SyscallReturn::Return(0))
+}
+
+/// Reads the absolute timeout of `mq_timedsend` or `mq_timedreceive`, which is measured
+/// against `CLOCK_REALTIME`.
+pub(super) fn read_mq_timeout(
+    abs_timeout_addr: Vaddr,
+    ctx: &Context,
+) -> Result<Option<ManagedTimeout<'static>>> {
+    if abs_timeout_addr == 0 {
+        return Ok(None);
+    }
+
+    let timespec = ctx.user_space().read_val::<timespec_t>(abs_timeout_addr)?;
+    let abs_timeout = Duration::try_from(timespec)?;
+    Ok(Some(ManagedTimeout::new_with_manager(
+        Timeout::When(abs_timeout),
+        RealTimeClock::timer_manager(),
+    )))
+}
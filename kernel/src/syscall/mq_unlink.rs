@@ -0,0 +1,14 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{mq_open::read_mq_name, SyscallReturn};
+use crate::{fs::mqueue::MqueueFs, prelude::*};
+
+pub fn sys_mq_unlink(name_addr: Vaddr, ctx: &Context) -> Result<SyscallReturn> {
+    let name = read_mq_name(name_addr, ctx)?;
+    debug!("[sys_mq_unlink] name = {:?}", name);
+
+    // The queue is destroyed after all its descriptors are closed.
+    MqueueFs::root_dentry().unlink(&name)?;
+
+    Ok(SyscallReturn::Return(0))
+}
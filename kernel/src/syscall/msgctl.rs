@@ -0,0 +1,94 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::SyscallReturn;
+use crate::{
+    ipc::{
+        ids::IpcObject,
+        msg::{msg_queues, MSGMNB},
+        CIpcPerm, IpcControlCmd, PermissionMode, IPC_64,
+    },
+    prelude::*,
+    process::{credentials::capabilities::CapSet, Gid, Uid},
+};
+
+pub fn sys_msgctl(msqid: i32, cmd: i32, buf: Vaddr, ctx: &Context) -> Result<SyscallReturn> {
+    let cmd = IpcControlCmd::try_from(cmd & !IPC_64)?;
+    debug!(
+        "[sys_msgctl] msqid = {}, cmd = {:?}, buf = {:#x}",
+        msqid, cmd, buf
+    );
+
+    let credentials = ctx.posix_thread.credentials();
+
+    match cmd {
+        IpcControlCmd::IPC_RMID => {
+            let queue =
+                msg_queues().remove(msqid, |queue| queue.permission().check_owner(&credentials))?;
+            queue.set_removed();
+        }
+        IpcControlCmd::IPC_SET => {
+            let msq_ds = ctx.user_space().read_val::<CMsqidDs>(buf)?;
+
+            let queue = msg_queues().get(msqid)?;
+            let permission = queue.permission();
+            permission.check_owner(&credentials)?;
+
+            let qbytes = msq_ds.msg_qbytes as usize;
+            if qbytes > MSGMNB
+                && !credentials
+                    .effective_capset()
+                    .contains(CapSet::SYS_RESOURCE)
+            {
+                return_errno_with_message!(Errno::EPERM, "the queue size exceeds the limit");
+            }
+
+            permission.set(
+                Uid::new(msq_ds.msg_perm.uid),
+                Gid::new(msq_ds.msg_perm.gid),
+                msq_ds.msg_perm.mode as u16,
+            );
+            queue.set_qbytes(qbytes);
+            queue.update_ctime();
+        }
+        IpcControlCmd::IPC_STAT => {
+            let queue = msg_queues().get(msqid)?;
+            queue
+                .permission()
+                .check_access(&credentials, PermissionMode::READ)?;
+
+            let stat = queue.stat();
+            let msq_ds = CMsqidDs {
+                msg_perm: queue.permission().to_c_perm(),
+                msg_stime: stat.stime.as_secs() as i64,
+                msg_rtime: stat.rtime.as_secs() as i64,
+                msg_ctime: stat.ctime.as_secs() as i64,
+                msg_cbytes: stat.cbytes as u64,
+                msg_qnum: stat.qnum as u64,
+                msg_qbytes: stat.qbytes as u64,
+                msg_lspid: stat.lspid,
+                msg_lrpid: stat.lrpid,
+                ..CMsqidDs::new_zeroed()
+            };
+            ctx.user_space().write_val(buf, &msq_ds)?;
+        }
+        _ => return_errno_with_message!(Errno::EINVAL, "the command is not supported"),
+    }
+
+    Ok(SyscallReturn::Return(0))
+}
+
+/// The data structure of a message queue (`struct msqid64_ds` in Linux).
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct CMsqidDs {
+    msg_perm: CIpcPerm,
+    msg_stime: i64,
+    msg_rtime: i64,
+    msg_ctime: i64,
+    msg_cbytes: u64,
+    msg_qnum: u64,
+    msg_qbytes: u64,
+    msg_lspid: u32,
+    msg_lrpid: u32,
+    _unused: [u64; 2],
+}
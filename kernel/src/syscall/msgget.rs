@@ -0,0 +1,33 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::SyscallReturn;
+use crate::{
+    ipc::{
+        ids::IpcObject,
+        key_t,
+        msg::{msg_queues, MsgQueue},
+        IpcFlags, PermissionMode,
+    },
+    prelude::*,
+};
+
+pub fn sys_msgget(key: key_t, msgflg: i32, ctx: &Context) -> Result<SyscallReturn> {
+    let flags = IpcFlags::from_bits_truncate(msgflg as u32);
+    let mode = (msgflg as u32 & 0o777) as u16;
+    let credentials = ctx.posix_thread.credentials();
+
+    debug!("[sys_msgget] key = {}, flags = {:?}", key, msgflg);
+
+    let id = msg_queues().get_or_create(
+        key,
+        flags,
+        |queue| {
+            queue
+                .permission()
+                .check_access(&credentials, PermissionMode::from_requested_mode(mode))
+        },
+        |_| Ok(MsgQueue::new(key, mode, &credentials)),
+    )?;
+
+    Ok(SyscallReturn::Return(id as _))
+}
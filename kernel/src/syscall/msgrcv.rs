@@ -0,0 +1,54 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::SyscallReturn;
+use crate::{
+    ipc::{
+        ids::IpcObject,
+        msg::{msg_queues, MsgFlags, MsgSelector},
+        PermissionMode,
+    },
+    prelude::*,
+};
+
+pub fn sys_msgrcv(
+    msqid: i32,
+    msgp: Vaddr,
+    msgsz: usize,
+    msgtyp: i64,
+    msgflg: i32,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let flags = MsgFlags::from_bits_truncate(msgflg as u32);
+    debug!(
+        "[sys_msgrcv] msqid = {}, msgp = {:#x}, msgsz = {}, msgtyp = {}, flags = {:?}",
+        msqid, msgp, msgsz, msgtyp, flags
+    );
+
+    if (msgsz as isize) < 0 {
+        return_errno_with_message!(Errno::EINVAL, "the message size is negative");
+    }
+    let selector = MsgSelector::new(msgtyp, flags)?;
+
+    let queue = msg_queues().get(msqid)?;
+    queue
+        .permission()
+        .check_access(&ctx.posix_thread.credentials(), PermissionMode::READ)?;
+
+    let (mtype, data) = queue.recv(
+        selector,
+        msgsz,
+        flags.contains(MsgFlags::MSG_NOERROR),
+        flags.contains(MsgFlags::IPC_NOWAIT),
+        ctx.process.pid(),
+    )?;
+
+    // The layout of the message is `struct msgbuf { long mtype; char mtext[]; }`.
+    let user_space = ctx.user_space();
+    user_space.write_val(msgp, &mtype)?;
+    user_space.write_bytes(
+        msgp + size_of::<i64>(),
+        &mut VmReader::from(data.as_slice()),
+    )?;
+
+    Ok(SyscallReturn::Return(data.len() as _))
+}
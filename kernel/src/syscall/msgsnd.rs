@@ -0,0 +1,51 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::SyscallReturn;
+use crate::{
+    ipc::{
+        ids::IpcObject,
+        msg::{msg_queues, Message, MsgFlags, MSGMAX},
+        PermissionMode,
+    },
+    prelude::*,
+};
+
+pub fn sys_msgsnd(
+    msqid: i32,
+    msgp: Vaddr,
+    msgsz: usize,
+    msgflg: i32,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let flags = MsgFlags::from_bits_truncate(msgflg as u32);
+    debug!(
+        "[sys_msgsnd] msqid = {}, msgp = {:#x}, msgsz = {}, flags = {:?}",
+        msqid, msgp, msgsz, flags
+    );
+
+    if msgsz > MSGMAX {
+        return_errno_with_message!(Errno::EINVAL, "the message is too long");
+    }
+
+    let queue = msg_queues().get(msqid)?;
+    queue
+        .permission()
+        .check_access(&ctx.posix_thread.credentials(), PermissionMode::WRITE)?;
+
+    // The layout of the message is `struct msgbuf { long mtype; char mtext[]; }`.
+    let user_space = ctx.user_space();
+    let mtype = user_space.read_val::<i64>(msgp)?;
+    if mtype < 1 {
+        return_errno_with_message!(Errno::EINVAL, "the message type is not positive");
+    }
+    let mut data = vec![0u8; msgsz].into_boxed_slice();
+    user_space.read_bytes(msgp + size_of::<i64>(), &mut VmWriter::from(data.as_mut()))?;
+
+    queue.send(
+        Message::new(mtype, data),
+        flags.contains(MsgFlags::IPC_NOWAIT),
+        ctx.process.pid(),
+    )?;
+
+    Ok(SyscallReturn::Return(0))
+}
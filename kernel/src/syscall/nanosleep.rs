@@ -9,7 +9,8 @@ use crate::{
     prelude::*,
     time::{
         clockid_t,
-        clocks::{BootTimeClock, MonotonicClock, RealTimeClock},
+        clocks::{BootTimeClock, MonotonicClock, RealTimeClock, TaiClock},
+        compat_timespec_t,
         timer::Timeout,
         timespec_t,
         wait::ManagedTimeout,
@@ -29,6 +30,25 @@ pub fn sys_nanosleep(
         false,
         request_timespec_addr,
         remain_timespec_addr,
+        false,
+        ctx,
+    )
+}
+
+/// The `nanosleep` system call of 32-bit programs.
+pub fn compat_sys_nanosleep(
+    request_timespec_addr: Vaddr,
+    remain_timespec_addr: Vaddr,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let clockid = ClockId::CLOCK_MONOTONIC;
+
+    do_clock_nanosleep(
+        clockid as clockid_t,
+        false,
+        request_timespec_addr,
+        remain_timespec_addr,
+        true,
         ctx,
     )
 }
@@ -53,6 +73,31 @@ pub fn sys_clock_nanosleep(
         is_abs_time,
         request_timespec_addr,
         remain_timespec_addr,
+        false,
+        ctx,
+    )
+}
+
+/// The `clock_nanosleep` system call of 32-bit programs.
+pub fn compat_sys_clock_nanosleep(
+    clockid: clockid_t,
+    flags: i32,
+    request_timespec_addr: Vaddr,
+    remain_timespec_addr: Vaddr,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let is_abs_time = match flags {
+        0 => false,
+        TIMER_ABSTIME => true,
+        _ => return_errno_with_message!(Errno::EINVAL, "invalid flags"),
+    };
+
+    do_clock_nanosleep(
+        clockid,
+        is_abs_time,
+        request_timespec_addr,
+        remain_timespec_addr,
+        true,
         ctx,
     )
 }
@@ -62,12 +107,18 @@ fn do_clock_nanosleep(
     is_abs_time: bool,
     request_timespec_addr: Vaddr,
     remain_timespec_addr: Vaddr,
+    is_compat: bool,
     ctx: &Context,
 ) -> Result<SyscallReturn> {
     let request_time = {
-        let timespec = ctx
-            .user_space()
-            .read_val::<timespec_t>(request_timespec_addr)?;
+        let user_space = ctx.user_space();
+        let timespec = if is_compat {
+            user_space
+                .read_val::<compat_timespec_t>(request_timespec_addr)?
+                .into()
+        } else {
+            user_space.read_val::<timespec_t>(request_timespec_addr)?
+        };
         Duration::try_from(timespec)?
     };
 
@@ -97,6 +148,7 @@ fn do_clock_nanosleep(
             ClockId::CLOCK_BOOTTIME => BootTimeClock::timer_manager(),
             ClockId::CLOCK_MONOTONIC => MonotonicClock::timer_manager(),
             ClockId::CLOCK_REALTIME => RealTimeClock::timer_manager(),
+            ClockId::CLOCK_TAI => TaiClock::timer_manager(),
             // FIXME: We should better not expose this prof timer manager.
             ClockId::CLOCK_PROCESS_CPUTIME_ID => {
                 ctx.process.timer_manager().prof_timer().timer_manager()
@@ -125,9 +177,15 @@ fn do_clock_nanosleep(
 
             if remain_timespec_addr != 0 && !is_abs_time {
                 let remaining_duration = (start_time + duration) - end_time;
-                let remaining_timespec = timespec_t::from(remaining_duration);
-                ctx.user_space()
-                    .write_val(remain_timespec_addr, &remaining_timespec)?;
+                if is_compat {
+                    let remaining_timespec = compat_timespec_t::from(remaining_duration);
+                    ctx.user_space()
+                        .write_val(remain_timespec_addr, &remaining_timespec)?;
+                } else {
+                    let remaining_timespec = timespec_t::from(remaining_duration);
+                    ctx.user_space()
+                        .write_val(remain_timespec_addr, &remaining_timespec)?;
+                }
             }
 
             return_errno_with_message!(Errno::EINTR, "sleep was interrupted");
@@ -1,10 +1,12 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use core::mem;
+
 use super::SyscallReturn;
 use crate::{
     fs::{
         file_table::{FdFlags, FileDesc},
-        fs_resolver::{FsPath, AT_FDCWD},
+        fs_resolver::{FsPath, ResolveFlags, AT_FDCWD},
         utils::{AccessMode, CreationFlags},
     },
     prelude::*,
@@ -24,16 +26,80 @@ pub fn sys_openat(
         dirfd, path, flags, mode
     );
 
+    do_open(
+        dirfd,
+        &path.to_string_lossy(),
+        flags,
+        mode,
+        ResolveFlags::empty(),
+        ctx,
+    )
+}
+
+pub fn sys_openat2(
+    dirfd: FileDesc,
+    path_addr: Vaddr,
+    how_addr: Vaddr,
+    size: usize,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let user_space = ctx.user_space();
+    let path = user_space.read_cstring(path_addr, MAX_FILENAME_LEN)?;
+    let how = read_open_how_from_user(how_addr, size, ctx)?;
+    debug!("dirfd = {}, path = {:?}, how = {:?}", dirfd, path, how);
+
+    let flags = u32::try_from(how.flags)
+        .map_err(|_| Error::with_message(Errno::EINVAL, "invalid flags"))?;
+    let creation_flags = CreationFlags::from_bits_truncate(flags);
+    if how.mode & !0o7777 != 0 {
+        return_errno_with_message!(Errno::EINVAL, "invalid mode");
+    }
+    if how.mode != 0
+        && !creation_flags.intersects(CreationFlags::O_CREAT | CreationFlags::_O_TMPFILE)
+    {
+        return_errno_with_message!(Errno::EINVAL, "mode is given without creating a file");
+    }
+
+    let resolve = ResolveFlags::from_bits(how.resolve)
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid resolve flags"))?;
+    if resolve.contains(ResolveFlags::RESOLVE_BENEATH | ResolveFlags::RESOLVE_IN_ROOT) {
+        return_errno_with_message!(
+            Errno::EINVAL,
+            "RESOLVE_BENEATH and RESOLVE_IN_ROOT are exclusive"
+        );
+    }
+    if resolve.contains(ResolveFlags::RESOLVE_CACHED) {
+        // The lookups may block on I/O, so a cached-only lookup is never guaranteed.
+        return_errno_with_message!(Errno::EAGAIN, "the lookup cannot be done in the cache");
+    }
+
+    let path = path.to_string_lossy();
+    let path = if resolve.contains(ResolveFlags::RESOLVE_IN_ROOT) {
+        // The absolute paths are resolved from `dirfd`, which is treated as the root.
+        path.trim_start_matches('/')
+    } else {
+        path.as_ref()
+    };
+    do_open(dirfd, path, flags, how.mode as u16, resolve, ctx)
+}
+
+fn do_open(
+    dirfd: FileDesc,
+    path: &str,
+    flags: u32,
+    mode: u16,
+    resolve: ResolveFlags,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
     let current = ctx.posix_thread;
     let file_handle = {
-        let path = path.to_string_lossy();
-        let fs_path = FsPath::new(dirfd, path.as_ref())?;
+        let fs_path = FsPath::new(dirfd, path)?;
         let mask_mode = mode & !current.fs().umask().read().get();
         let inode_handle = current
             .fs()
             .resolver()
             .read()
-            .open(&fs_path, flags, mask_mode)
+            .open_with_resolve(&fs_path, flags, mask_mode, resolve)
             .map_err(|err| match err.error() {
                 Errno::EINTR => Error::new(Errno::ERESTARTSYS),
                 _ => err,
@@ -50,7 +116,7 @@ pub fn sys_openat(
             } else {
                 FdFlags::empty()
             };
-        file_table_locked.insert(file_handle, fd_flags)
+        file_table_locked.insert(file_handle, fd_flags)?
     };
 
     Ok(SyscallReturn::Return(fd as _))
@@ -65,3 +131,39 @@ pub fn sys_creat(path_addr: Vaddr, mode: u16, ctx: &Context) -> Result<SyscallRe
         AccessMode::O_WRONLY as u32 | CreationFlags::O_CREAT.bits() | CreationFlags::O_TRUNC.bits();
     self::sys_openat(AT_FDCWD, path_addr, flags, mode, ctx)
 }
+
+/// The arguments of `openat2`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Default)]
+struct OpenHow {
+    flags: u64,
+    mode: u64,
+    resolve: u64,
+}
+
+/// Reads the `OpenHow`, whose size may be extended by the newer versions.
+///
+/// The extended bytes that are unknown must be zero.
+fn read_open_how_from_user(addr: Vaddr, size: usize, ctx: &Context) -> Result<OpenHow> {
+    let type_size = mem::size_of::<OpenHow>();
+    if size < type_size {
+        return_errno_with_message!(Errno::EINVAL, "too small open_how");
+    }
+    if size > PAGE_SIZE {
+        return_errno_with_message!(Errno::E2BIG, "too big open_how");
+    }
+
+    let space = ctx.user_space();
+    let how: OpenHow = space.read_val(addr)?;
+
+    if size > type_size {
+        let mut buf = vec![0; size - type_size];
+        space.read_bytes(addr + type_size, &mut VmWriter::from(&mut *buf))?;
+
+        if buf.iter().any(|&b| b != 0) {
+            return_errno_with_message!(Errno::E2BIG, "unknown fields in open_how");
+        }
+    }
+
+    Ok(how)
+}
@@ -0,0 +1,187 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::mem::size_of;
+
+use ostd::cpu::{num_cpus, CpuId};
+
+use super::SyscallReturn;
+use crate::{
+    fs::file_table::{FdFlags, FileDesc},
+    perf::{
+        attr::{PerfAttrFlags, PerfEventAttr, PerfReadFormat, PerfSampleType, PERF_ATTR_SIZE_VER0},
+        PerfEvent,
+    },
+    prelude::*,
+    process::{
+        credentials::capabilities::CapSet,
+        posix_thread::{thread_table, AsPosixThread},
+    },
+    thread::Thread,
+};
+
+pub fn sys_perf_event_open(
+    attr_addr: Vaddr,
+    pid: i32,
+    cpu: i32,
+    group_fd: i32,
+    flags: u64,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    debug!(
+        "attr_addr = {:#x}, pid = {}, cpu = {}, group_fd = {}, flags = {:#x}",
+        attr_addr, pid, cpu, group_fd, flags
+    );
+
+    if flags & !PERF_FLAG_FD_CLOEXEC != 0 {
+        return_errno_with_message!(Errno::EINVAL, "unsupported perf_event_open flags");
+    }
+    if group_fd != -1 {
+        return_errno_with_message!(Errno::EINVAL, "perf event groups are not supported");
+    }
+
+    let attr = read_attr(attr_addr, ctx)?;
+    check_attr(&attr)?;
+
+    let thread = match pid {
+        0 => current_thread!(),
+        -1 => return_errno_with_message!(
+            Errno::EINVAL,
+            "perf events for all the threads on a CPU are not supported"
+        ),
+        _ if pid < 0 => return_errno_with_message!(Errno::EINVAL, "invalid pid"),
+        _ => thread_table::get_thread(pid as u32)
+            .ok_or_else(|| Error::with_message(Errno::ESRCH, "thread does not exist"))?,
+    };
+    let cpu = match cpu {
+        -1 => None,
+        _ if cpu < 0 || cpu as usize >= num_cpus() => {
+            return_errno_with_message!(Errno::EINVAL, "invalid CPU")
+        }
+        _ => Some(CpuId::try_from(cpu as usize).unwrap()),
+    };
+
+    check_permission(&attr, &thread, ctx)?;
+
+    let event = PerfEvent::new(&attr, thread, cpu)?;
+    // A perf event is always opened with `O_CLOEXEC`, as Linux does.
+    let fd: FileDesc = {
+        let file_table = ctx.thread_local.file_table().borrow();
+        let mut file_table_locked = file_table.write();
+        file_table_locked.insert(event, FdFlags::CLOEXEC)?
+    };
+
+    Ok(SyscallReturn::Return(fd as _))
+}
+
+/// Reads the attributes, which may be of an older or newer version.
+///
+/// If the attributes are too big, the size of the supported attributes is written back to the
+/// user space, as Linux does.
+fn read_attr(addr: Vaddr, ctx: &Context) -> Result<PerfEventAttr> {
+    let user_space = ctx.user_space();
+    let size_addr = addr + size_of::<u32>();
+    let size = match user_space.read_val::<u32>(size_addr)? as usize {
+        0 => PERF_ATTR_SIZE_VER0,
+        size if !(PERF_ATTR_SIZE_VER0..=PAGE_SIZE).contains(&size) => {
+            user_space.write_val(size_addr, &(size_of::<PerfEventAttr>() as u32))?;
+            return_errno_with_message!(Errno::E2BIG, "invalid size of the attributes")
+        }
+        size => size,
+    };
+
+    let mut attr = PerfEventAttr::new_zeroed();
+    let len = size.min(size_of::<PerfEventAttr>());
+    user_space.read_bytes(addr, &mut VmWriter::from(&mut attr.as_bytes_mut()[..len]))?;
+
+    if size > size_of::<PerfEventAttr>() {
+        let mut extension = vec![0u8; size - size_of::<PerfEventAttr>()];
+        user_space.read_bytes(
+            addr + size_of::<PerfEventAttr>(),
+            &mut VmWriter::from(extension.as_mut_slice()),
+        )?;
+        if extension.iter().any(|&byte| byte != 0) {
+            user_space.write_val(size_addr, &(size_of::<PerfEventAttr>() as u32))?;
+            return_errno_with_message!(Errno::E2BIG, "unknown attributes");
+        }
+    }
+
+    Ok(attr)
+}
+
+fn check_attr(attr: &PerfEventAttr) -> Result<()> {
+    let Some(flags) = PerfAttrFlags::from_bits(attr.flags) else {
+        return_errno_with_message!(Errno::EINVAL, "unknown attribute flags");
+    };
+    if flags.intersects(PerfAttrFlags::PRECISE_IP) {
+        return_errno_with_message!(Errno::EOPNOTSUPP, "precise IPs are not supported");
+    }
+    if flags.intersects(PerfAttrFlags::UNSUPPORTED) {
+        return_errno_with_message!(Errno::EINVAL, "unsupported attribute flags");
+    }
+    // The timestamps in the samples are always of `CLOCK_MONOTONIC`.
+    if flags.contains(PerfAttrFlags::USE_CLOCKID) && attr.clockid != CLOCK_MONOTONIC {
+        return_errno_with_message!(Errno::EINVAL, "unsupported clock for the timestamps");
+    }
+    if flags.intersects(PerfAttrFlags::SIDEBAND) {
+        debug!("the sideband records are not generated: {:?}", flags);
+    }
+    // The children do not inherit the events, which should be fine for `perf stat` and
+    // `perf record` that count a program that does not fork.
+    if flags.contains(PerfAttrFlags::INHERIT) {
+        debug!("the inherited perf events are not supported");
+    }
+
+    if flags.contains(PerfAttrFlags::FREQ) && attr.sample_period > MAX_SAMPLE_FREQ {
+        return_errno_with_message!(Errno::EINVAL, "the sampling frequency is too high");
+    }
+    if PerfSampleType::from_bits(attr.sample_type).is_none() {
+        return_errno_with_message!(Errno::EINVAL, "unsupported sample fields");
+    }
+    if PerfReadFormat::from_bits(attr.read_format).is_none() {
+        return_errno_with_message!(Errno::EINVAL, "unsupported read format");
+    }
+    if attr.branch_sample_type != 0 || attr.sample_regs_user != 0 || attr.sample_stack_user != 0 {
+        return_errno_with_message!(Errno::EINVAL, "unsupported sample fields");
+    }
+
+    Ok(())
+}
+
+/// Checks whether the current thread can count the events in the target thread.
+///
+/// Like Linux with `perf_event_paranoid` set to 2, the kernel events can only be counted with
+/// `CAP_PERFMON`, and the events of another user can never be counted without it.
+fn check_permission(attr: &PerfEventAttr, thread: &Thread, ctx: &Context) -> Result<()> {
+    let credentials = ctx.posix_thread.credentials();
+    let capset = credentials.effective_capset();
+    if capset.contains(CapSet::PERFMON) || capset.contains(CapSet::SYS_ADMIN) {
+        return Ok(());
+    }
+
+    let flags = PerfAttrFlags::from_bits_truncate(attr.flags);
+    if !flags.contains(PerfAttrFlags::EXCLUDE_KERNEL) {
+        return_errno_with_message!(
+            Errno::EACCES,
+            "CAP_PERFMON is required to count the kernel events"
+        );
+    }
+
+    let Some(target) = thread.as_posix_thread() else {
+        return_errno_with_message!(Errno::ESRCH, "thread does not exist");
+    };
+    if target.credentials().euid() != credentials.euid() {
+        return_errno_with_message!(
+            Errno::EACCES,
+            "CAP_PERFMON is required to count the events of another user"
+        );
+    }
+
+    Ok(())
+}
+
+const PERF_FLAG_FD_CLOEXEC: u64 = 1 << 3;
+
+const CLOCK_MONOTONIC: i32 = 1;
+
+/// The maximum sampling frequency, i.e., the default of `perf_event_max_sample_rate`.
+const MAX_SAMPLE_FREQ: u64 = 100_000;
@@ -3,9 +3,10 @@
 use super::SyscallReturn;
 use crate::{
     fs::{
+        file_handle::FileLike,
         file_table::{FdFlags, FileDesc},
         pipe,
-        utils::CreationFlags,
+        utils::{CreationFlags, StatusFlags},
     },
     prelude::*,
 };
@@ -15,6 +16,11 @@ pub fn sys_pipe2(fds: Vaddr, flags: u32, ctx: &Context) -> Result<SyscallReturn>
 
     let (pipe_reader, pipe_writer) = pipe::new_pair()?;
 
+    let status_flags =
+        StatusFlags::from_bits_truncate(flags) & (StatusFlags::O_NONBLOCK | StatusFlags::O_DIRECT);
+    pipe_reader.set_status_flags(status_flags)?;
+    pipe_writer.set_status_flags(status_flags)?;
+
     let fd_flags = if CreationFlags::from_bits_truncate(flags).contains(CreationFlags::O_CLOEXEC) {
         FdFlags::CLOEXEC
     } else {
@@ -24,9 +30,17 @@ pub fn sys_pipe2(fds: Vaddr, flags: u32, ctx: &Context) -> Result<SyscallReturn>
     let file_table = ctx.thread_local.file_table().borrow();
     let mut file_table_locked = file_table.write();
 
+    let reader_fd = file_table_locked.insert(pipe_reader, fd_flags)?;
+    let writer_fd = match file_table_locked.insert(pipe_writer, fd_flags) {
+        Ok(writer_fd) => writer_fd,
+        Err(err) => {
+            file_table_locked.close_file(reader_fd).unwrap();
+            return Err(err);
+        }
+    };
     let pipe_fds = PipeFds {
-        reader_fd: file_table_locked.insert(pipe_reader, fd_flags),
-        writer_fd: file_table_locked.insert(pipe_writer, fd_flags),
+        reader_fd,
+        writer_fd,
     };
     debug!("pipe_fds: {:?}", pipe_fds);
 
@@ -1,9 +1,22 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use super::SyscallReturn;
+use ostd::mm::MAX_USERSPACE_VADDR;
+
+use super::{
+    seccomp::{set_mode_filter, set_mode_strict},
+    SyscallReturn,
+};
 use crate::{
+    fs::file_table::{get_file_fast, FileDesc},
     prelude::*,
-    process::{posix_thread::MAX_THREAD_NAME_LEN, signal::sig_num::SigNum},
+    process::{
+        check_executable_file,
+        credentials::capabilities::CapSet,
+        posix_thread::{SyscallUserDispatchConfig, MAX_THREAD_NAME_LEN},
+        signal::sig_num::SigNum,
+        MmLayout, ResourceType,
+    },
+    vm::vmar::is_userspace_vaddr,
 };
 
 pub fn sys_prctl(
@@ -93,11 +106,232 @@ pub fn sys_prctl(
             ctx.user_space()
                 .write_val(write_addr, &(process.is_child_subreaper() as u32))?;
         }
+        PrctlCmd::PR_SET_NO_NEW_PRIVS => {
+            ctx.posix_thread.credentials_mut().set_no_new_privs();
+        }
+        PrctlCmd::PR_GET_NO_NEW_PRIVS => {
+            let no_new_privs = ctx.posix_thread.credentials().no_new_privs();
+            return Ok(SyscallReturn::Return(no_new_privs as _));
+        }
+        PrctlCmd::PR_GET_SECCOMP => {
+            let mode = ctx.posix_thread.credentials().seccomp_mode();
+            return Ok(SyscallReturn::Return(mode.as_u32() as _));
+        }
+        PrctlCmd::PR_SET_SECCOMP(SeccompModeArg::Strict) => set_mode_strict(ctx)?,
+        PrctlCmd::PR_SET_SECCOMP(SeccompModeArg::Filter(prog_addr)) => {
+            set_mode_filter(0, prog_addr, ctx)?
+        }
+        PrctlCmd::PR_SET_SYSCALL_USER_DISPATCH(config) => {
+            let syscall_user_dispatch = ctx.thread_local.syscall_user_dispatch();
+            match config {
+                Some(config) => syscall_user_dispatch.enable(config),
+                None => syscall_user_dispatch.disable(),
+            }
+        }
+        PrctlCmd::PR_SET_MM(cmd) => set_mm(cmd, ctx)?,
         _ => todo!(),
     }
     Ok(SyscallReturn::Return(0))
 }
 
+/// Changes the addresses in the `MmLayout` and the heap, the auxiliary vector, or the
+/// executable file of the current process, which is used to restore a process from a
+/// checkpoint.
+fn set_mm(cmd: SetMmCmd, ctx: &Context) -> Result<()> {
+    let credentials = ctx.posix_thread.credentials();
+    let process_vm = ctx.process.vm();
+    let heap = ctx.process.heap();
+
+    let (addr, size) = match cmd {
+        SetMmCmd::MapSize(addr) => {
+            ctx.user_space()
+                .write_val(addr, &(size_of::<PrctlMmMap>() as u32))?;
+            return Ok(());
+        }
+        SetMmCmd::Map { addr, size } => (addr, size),
+        _ => {
+            // Like Linux, the fields can be changed one by one only with `CAP_SYS_RESOURCE`,
+            // while `PR_SET_MM_MAP` validates all of them at once instead.
+            if !credentials
+                .effective_capset()
+                .contains(CapSet::SYS_RESOURCE)
+            {
+                return_errno_with_message!(
+                    Errno::EPERM,
+                    "`CAP_SYS_RESOURCE` is required to change the fields"
+                );
+            }
+            drop(credentials);
+
+            let mut mm_layout = process_vm.mm_layout().lock();
+            let mut new_layout = mm_layout.clone();
+            let (mut start_brk, mut brk) = (heap.base(), heap.current_heap_end());
+            match cmd {
+                SetMmCmd::Field(field, addr) => match field {
+                    MmField::StartCode => new_layout.start_code = addr,
+                    MmField::EndCode => new_layout.end_code = addr,
+                    MmField::StartData => new_layout.start_data = addr,
+                    MmField::EndData => new_layout.end_data = addr,
+                    MmField::StartStack => new_layout.start_stack = addr,
+                    MmField::StartBrk => start_brk = addr,
+                    MmField::Brk => brk = addr,
+                    MmField::ArgStart => new_layout.arg_start = addr,
+                    MmField::ArgEnd => new_layout.arg_end = addr,
+                    MmField::EnvStart => new_layout.env_start = addr,
+                    MmField::EnvEnd => new_layout.env_end = addr,
+                },
+                SetMmCmd::Auxv { addr, size } => {
+                    mm_layout.auxv = read_auxv(addr, size, ctx)?;
+                    return Ok(());
+                }
+                SetMmCmd::ExeFile(fd) => {
+                    drop(mm_layout);
+                    return set_exe_file(fd, ctx);
+                }
+                SetMmCmd::Map { .. } | SetMmCmd::MapSize(_) => unreachable!(),
+            }
+
+            check_mm_layout(&new_layout, start_brk, brk, ctx)?;
+            heap.set_range(start_brk, brk)?;
+            *mm_layout = new_layout;
+            return Ok(());
+        }
+    };
+
+    if size != size_of::<PrctlMmMap>() {
+        return_errno_with_message!(
+            Errno::EINVAL,
+            "the size of `struct prctl_mm_map` is invalid"
+        );
+    }
+    let mm_map: PrctlMmMap = ctx.user_space().read_val(addr)?;
+
+    let mut mm_layout = process_vm.mm_layout().lock();
+    let mut new_layout = mm_layout.clone();
+    new_layout.start_code = mm_map.start_code as Vaddr;
+    new_layout.end_code = mm_map.end_code as Vaddr;
+    new_layout.start_data = mm_map.start_data as Vaddr;
+    new_layout.end_data = mm_map.end_data as Vaddr;
+    new_layout.start_stack = mm_map.start_stack as Vaddr;
+    new_layout.arg_start = mm_map.arg_start as Vaddr;
+    new_layout.arg_end = mm_map.arg_end as Vaddr;
+    new_layout.env_start = mm_map.env_start as Vaddr;
+    new_layout.env_end = mm_map.env_end as Vaddr;
+    let (start_brk, brk) = (mm_map.start_brk as Vaddr, mm_map.brk as Vaddr);
+    check_mm_layout(&new_layout, start_brk, brk, ctx)?;
+
+    if mm_map.auxv_size != 0 {
+        new_layout.auxv = read_auxv(mm_map.auxv as Vaddr, mm_map.auxv_size as usize, ctx)?;
+    }
+    if mm_map.exe_fd != u32::MAX {
+        let capset = credentials.effective_capset();
+        if !capset.intersects(CapSet::CHECKPOINT_RESTORE | CapSet::SYS_ADMIN) {
+            return_errno_with_message!(
+                Errno::EPERM,
+                "`CAP_CHECKPOINT_RESTORE` is required to change the executable file"
+            );
+        }
+        drop(credentials);
+        set_exe_file(mm_map.exe_fd as FileDesc, ctx)?;
+    }
+
+    heap.set_range(start_brk, brk)?;
+    *mm_layout = new_layout;
+    Ok(())
+}
+
+/// Checks that the addresses are in the user space and in order, and that the data does not
+/// exceed `RLIMIT_DATA`, as Linux's `validate_prctl_map_addr` does.
+fn check_mm_layout(
+    mm_layout: &MmLayout,
+    start_brk: Vaddr,
+    brk: Vaddr,
+    ctx: &Context,
+) -> Result<()> {
+    let addrs = [
+        mm_layout.start_code,
+        mm_layout.end_code,
+        mm_layout.start_data,
+        mm_layout.end_data,
+        mm_layout.start_stack,
+        start_brk,
+        brk,
+        mm_layout.arg_start,
+        mm_layout.arg_end,
+        mm_layout.env_start,
+        mm_layout.env_end,
+    ];
+    if !addrs.into_iter().all(is_userspace_vaddr) {
+        return_errno_with_message!(Errno::EINVAL, "the address is not in the user space");
+    }
+
+    if mm_layout.start_code >= mm_layout.end_code
+        || mm_layout.start_data > mm_layout.end_data
+        || start_brk > brk
+        || mm_layout.arg_start > mm_layout.arg_end
+        || mm_layout.env_start > mm_layout.env_end
+    {
+        return_errno_with_message!(Errno::EINVAL, "the addresses are not in order");
+    }
+
+    let rlimit_data = ctx
+        .process
+        .resource_limits()
+        .get_rlimit(ResourceType::RLIMIT_DATA)
+        .get_cur();
+    let data_size = (brk - start_brk) + (mm_layout.end_data - mm_layout.start_data);
+    if data_size as u64 > rlimit_data {
+        return_errno_with_message!(Errno::EINVAL, "the data exceeds `RLIMIT_DATA`");
+    }
+
+    Ok(())
+}
+
+/// The maximum number of the words in the auxiliary vector, i.e., `AT_VECTOR_SIZE` of Linux.
+const MAX_AUXV_WORDS: usize = 60;
+
+/// Reads the auxiliary vector, and terminates it with `AT_NULL` if it is not terminated.
+fn read_auxv(addr: Vaddr, size: usize, ctx: &Context) -> Result<Vec<u64>> {
+    if addr == 0 || size > MAX_AUXV_WORDS * size_of::<u64>() {
+        return_errno_with_message!(Errno::EINVAL, "the auxiliary vector is invalid");
+    }
+
+    let user_space = ctx.user_space();
+    let mut auxv = Vec::with_capacity(size / size_of::<u64>() + 2);
+    for word_addr in
+        (addr..addr + size / size_of::<u64>() * size_of::<u64>()).step_by(size_of::<u64>())
+    {
+        auxv.push(user_space.read_val::<u64>(word_addr)?);
+    }
+
+    let is_terminated = auxv.chunks_exact(2).any(|pair| pair[0] == AT_NULL);
+    if !is_terminated {
+        auxv.truncate(auxv.len() / 2 * 2);
+        auxv.extend([AT_NULL, 0]);
+    }
+    Ok(auxv)
+}
+
+const AT_NULL: u64 = 0;
+
+/// Sets the executable file of the current process (i.e., `/proc/[pid]/exe`).
+fn set_exe_file(fd: FileDesc, ctx: &Context) -> Result<()> {
+    let path = {
+        let mut file_table = ctx.thread_local.file_table().borrow_mut();
+        let file = get_file_fast!(&mut file_table, fd);
+        let Ok(inode_handle) = file.as_inode_or_err() else {
+            return_errno_with_message!(Errno::EACCES, "the file is not a regular file");
+        };
+        if !inode_handle.dentry().type_().is_regular_file() {
+            return_errno_with_message!(Errno::EACCES, "the file is not a regular file");
+        }
+        check_executable_file(inode_handle.dentry())?;
+        inode_handle.dentry().abs_path()
+    };
+    ctx.process.set_executable_path(path);
+    Ok(())
+}
+
 const PR_SET_PDEATHSIG: i32 = 1;
 const PR_GET_PDEATHSIG: i32 = 2;
 const PR_GET_DUMPABLE: i32 = 3;
@@ -106,10 +340,16 @@ const PR_GET_KEEPCAPS: i32 = 7;
 const PR_SET_KEEPCAPS: i32 = 8;
 const PR_SET_NAME: i32 = 15;
 const PR_GET_NAME: i32 = 16;
+const PR_GET_SECCOMP: i32 = 21;
+const PR_SET_SECCOMP: i32 = 22;
 const PR_SET_TIMERSLACK: i32 = 29;
 const PR_GET_TIMERSLACK: i32 = 30;
 const PR_SET_CHILD_SUBREAPER: i32 = 36;
 const PR_GET_CHILD_SUBREAPER: i32 = 37;
+const PR_SET_MM: i32 = 35;
+const PR_SET_NO_NEW_PRIVS: i32 = 38;
+const PR_GET_NO_NEW_PRIVS: i32 = 39;
+const PR_SET_SYSCALL_USER_DISPATCH: i32 = 59;
 
 #[expect(non_camel_case_types)]
 #[derive(Debug, Clone, Copy)]
@@ -128,8 +368,83 @@ pub enum PrctlCmd {
     PR_GET_DUMPABLE,
     PR_SET_CHILD_SUBREAPER(bool),
     PR_GET_CHILD_SUBREAPER(Vaddr),
+    PR_SET_NO_NEW_PRIVS,
+    PR_GET_NO_NEW_PRIVS,
+    PR_GET_SECCOMP,
+    PR_SET_SECCOMP(SeccompModeArg),
+    /// Enables syscall user dispatch with the configuration, or disables it with `None`.
+    PR_SET_SYSCALL_USER_DISPATCH(Option<SyscallUserDispatchConfig>),
+    PR_SET_MM(SetMmCmd),
+}
+
+/// The operations of `PR_SET_MM`.
+#[derive(Debug, Clone, Copy)]
+pub enum SetMmCmd {
+    /// Sets an address of the code, the data, the heap, the stack, etc.
+    Field(MmField, Vaddr),
+    /// Sets the auxiliary vector (i.e., `PR_SET_MM_AUXV`).
+    Auxv { addr: Vaddr, size: usize },
+    /// Sets the executable file (i.e., `PR_SET_MM_EXE_FILE`).
+    ExeFile(FileDesc),
+    /// Sets all the fields in `struct prctl_mm_map` (i.e., `PR_SET_MM_MAP`).
+    Map { addr: Vaddr, size: usize },
+    /// Gets the size of `struct prctl_mm_map` (i.e., `PR_SET_MM_MAP_SIZE`).
+    MapSize(Vaddr),
 }
 
+#[repr(u64)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromInt)]
+pub enum MmField {
+    StartCode = 1,
+    EndCode = 2,
+    StartData = 3,
+    EndData = 4,
+    StartStack = 5,
+    StartBrk = 6,
+    Brk = 7,
+    ArgStart = 8,
+    ArgEnd = 9,
+    EnvStart = 10,
+    EnvEnd = 11,
+}
+
+const PR_SET_MM_AUXV: u64 = 12;
+const PR_SET_MM_EXE_FILE: u64 = 13;
+const PR_SET_MM_MAP: u64 = 14;
+const PR_SET_MM_MAP_SIZE: u64 = 15;
+
+/// The layout of a process for `PR_SET_MM_MAP` (i.e., `struct prctl_mm_map`).
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct PrctlMmMap {
+    start_code: u64,
+    end_code: u64,
+    start_data: u64,
+    end_data: u64,
+    start_brk: u64,
+    brk: u64,
+    start_stack: u64,
+    arg_start: u64,
+    arg_end: u64,
+    env_start: u64,
+    env_end: u64,
+    auxv: u64,
+    auxv_size: u32,
+    exe_fd: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum SeccompModeArg {
+    Strict,
+    Filter(Vaddr),
+}
+
+const SECCOMP_MODE_STRICT: u64 = 1;
+const SECCOMP_MODE_FILTER: u64 = 2;
+
+const PR_SYS_DISPATCH_OFF: u64 = 0;
+const PR_SYS_DISPATCH_ON: u64 = 1;
+
 #[repr(u64)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromInt)]
 pub enum Dumpable {
@@ -139,7 +454,7 @@ pub enum Dumpable {
 }
 
 impl PrctlCmd {
-    fn from_args(option: i32, arg2: u64, _arg3: u64, _arg4: u64, _arg5: u64) -> Result<PrctlCmd> {
+    fn from_args(option: i32, arg2: u64, arg3: u64, arg4: u64, arg5: u64) -> Result<PrctlCmd> {
         match option {
             PR_SET_PDEATHSIG => {
                 let signum = SigNum::try_from(arg2 as u8)?;
@@ -156,6 +471,77 @@ impl PrctlCmd {
             PR_SET_KEEPCAPS => Ok(PrctlCmd::PR_SET_KEEPCAPS(arg2 as _)),
             PR_SET_CHILD_SUBREAPER => Ok(PrctlCmd::PR_SET_CHILD_SUBREAPER(arg2 > 0)),
             PR_GET_CHILD_SUBREAPER => Ok(PrctlCmd::PR_GET_CHILD_SUBREAPER(arg2 as _)),
+            PR_SET_NO_NEW_PRIVS => {
+                // The flag cannot be cleared once it is set.
+                if arg2 != 1 {
+                    return_errno_with_message!(Errno::EINVAL, "no_new_privs cannot be cleared");
+                }
+                Ok(PrctlCmd::PR_SET_NO_NEW_PRIVS)
+            }
+            PR_GET_NO_NEW_PRIVS => Ok(PrctlCmd::PR_GET_NO_NEW_PRIVS),
+            PR_GET_SECCOMP => Ok(PrctlCmd::PR_GET_SECCOMP),
+            PR_SET_SECCOMP => match arg2 {
+                SECCOMP_MODE_STRICT => Ok(PrctlCmd::PR_SET_SECCOMP(SeccompModeArg::Strict)),
+                SECCOMP_MODE_FILTER => {
+                    Ok(PrctlCmd::PR_SET_SECCOMP(SeccompModeArg::Filter(arg3 as _)))
+                }
+                _ => return_errno_with_message!(Errno::EINVAL, "invalid seccomp mode"),
+            },
+            PR_SET_SYSCALL_USER_DISPATCH => {
+                let config = match arg2 {
+                    PR_SYS_DISPATCH_OFF => {
+                        if arg3 != 0 || arg4 != 0 || arg5 != 0 {
+                            return_errno_with_message!(
+                                Errno::EINVAL,
+                                "invalid arguments to disable syscall user dispatch"
+                            );
+                        }
+                        None
+                    }
+                    PR_SYS_DISPATCH_ON => {
+                        if arg3 != 0 && arg3.checked_add(arg4).is_none_or(|end| end <= arg3) {
+                            return_errno_with_message!(Errno::EINVAL, "the region overflows");
+                        }
+                        if arg5 as Vaddr >= MAX_USERSPACE_VADDR {
+                            return_errno_with_message!(
+                                Errno::EFAULT,
+                                "the selector is not in the user space"
+                            );
+                        }
+                        Some(SyscallUserDispatchConfig {
+                            offset: arg3 as Vaddr,
+                            len: arg4 as usize,
+                            selector: Some(arg5 as Vaddr).filter(|selector| *selector != 0),
+                        })
+                    }
+                    _ => return_errno_with_message!(
+                        Errno::EINVAL,
+                        "invalid mode of syscall user dispatch"
+                    ),
+                };
+                Ok(PrctlCmd::PR_SET_SYSCALL_USER_DISPATCH(config))
+            }
+            PR_SET_MM => {
+                let takes_arg4 =
+                    matches!(arg2, PR_SET_MM_AUXV | PR_SET_MM_MAP | PR_SET_MM_MAP_SIZE);
+                if arg5 != 0 || (arg4 != 0 && !takes_arg4) {
+                    return_errno_with_message!(Errno::EINVAL, "invalid arguments of `PR_SET_MM`");
+                }
+                let cmd = match arg2 {
+                    PR_SET_MM_AUXV => SetMmCmd::Auxv {
+                        addr: arg3 as _,
+                        size: arg4 as _,
+                    },
+                    PR_SET_MM_EXE_FILE => SetMmCmd::ExeFile(arg3 as _),
+                    PR_SET_MM_MAP => SetMmCmd::Map {
+                        addr: arg3 as _,
+                        size: arg4 as _,
+                    },
+                    PR_SET_MM_MAP_SIZE => SetMmCmd::MapSize(arg3 as _),
+                    field => SetMmCmd::Field(MmField::try_from(field)?, arg3 as _),
+                };
+                Ok(PrctlCmd::PR_SET_MM(cmd))
+            }
             _ => {
                 debug!("prctl cmd number: {}", option);
                 return_errno_with_message!(Errno::EINVAL, "unsupported prctl command");
@@ -41,3 +41,16 @@ pub fn sys_pread64(
 
     Ok(SyscallReturn::Return(read_len as _))
 }
+
+/// The `pread64` system call of 32-bit programs, whose offset is split into two registers.
+pub fn compat_sys_pread64(
+    fd: FileDesc,
+    user_buf_ptr: Vaddr,
+    user_buf_len: usize,
+    offset_low: u32,
+    offset_high: u32,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let offset = ((offset_high as u64) << 32 | offset_low as u64) as i64;
+    sys_pread64(fd, user_buf_ptr, user_buf_len, offset, ctx)
+}
@@ -3,7 +3,13 @@
 use super::SyscallReturn;
 use crate::{
     prelude::*,
-    process::{rlimit::RawRLimit64, Pid, ResourceType},
+    process::{
+        credentials::capabilities::CapSet,
+        posix_thread::AsPosixThread,
+        process_table,
+        rlimit::{nr_open, RawRLimit64},
+        Pid, Process, ResourceType,
+    },
 };
 
 pub fn sys_getrlimit(resource: u32, rlim_addr: Vaddr, ctx: &Context) -> Result<SyscallReturn> {
@@ -24,10 +30,7 @@ pub fn sys_setrlimit(resource: u32, new_rlim_addr: Vaddr, ctx: &Context) -> Resu
         resource, new_rlim_addr
     );
     let new_raw: RawRLimit64 = ctx.user_space().read_val(new_rlim_addr)?;
-    let resource_limits = ctx.process.resource_limits();
-    resource_limits
-        .get_rlimit(resource)
-        .set_cur_and_max(new_raw.cur, new_raw.max)?;
+    set_rlimit(ctx.process, resource, new_raw, ctx)?;
     Ok(SyscallReturn::Return(0))
 }
 
@@ -43,19 +46,92 @@ pub fn sys_prlimit64(
         "pid = {}, resource = {:?}, new_rlim_addr = 0x{:x}, old_rlim_addr = 0x{:x}",
         pid, resource, new_rlim_addr, old_rlim_addr
     );
-    let resource_limits = ctx.process.resource_limits();
+
+    let new_raw = if new_rlim_addr != 0 {
+        let new_raw: RawRLimit64 = ctx.user_space().read_val(new_rlim_addr)?;
+        debug!("new_rlimit = {:?}", new_raw);
+        Some(new_raw)
+    } else {
+        None
+    };
+
+    let target_process = if pid == 0 || pid == ctx.process.pid() {
+        None
+    } else {
+        let process = process_table::get_process(pid)
+            .ok_or_else(|| Error::with_message(Errno::ESRCH, "the process does not exist"))?;
+        check_prlimit_perm(&process, ctx)?;
+        Some(process)
+    };
+    let process = target_process.as_deref().unwrap_or(ctx.process);
+
+    let rlimit = process.resource_limits().get_rlimit(resource);
+    let (cur, max) = rlimit.get_cur_and_max();
+    if let Some(new_raw) = new_raw {
+        set_rlimit(process, resource, new_raw, ctx)?;
+    }
     if old_rlim_addr != 0 {
-        let rlimit = resource_limits.get_rlimit(resource);
-        let (cur, max) = rlimit.get_cur_and_max();
         let rlimit_raw = RawRLimit64 { cur, max };
         ctx.user_space().write_val(old_rlim_addr, &rlimit_raw)?;
     }
-    if new_rlim_addr != 0 {
-        let new_raw: RawRLimit64 = ctx.user_space().read_val(new_rlim_addr)?;
-        debug!("new_rlimit = {:?}", new_raw);
-        resource_limits
-            .get_rlimit(resource)
-            .set_cur_and_max(new_raw.cur, new_raw.max)?;
-    }
     Ok(SyscallReturn::Return(0))
 }
+
+/// Checks whether the current thread can get and set the resource limits of another process.
+///
+/// Like Linux, the real, effective, and saved user (group) IDs of the target process must be
+/// the same as the real user (group) ID of the current thread, unless the current thread
+/// has `CAP_SYS_RESOURCE`.
+fn check_prlimit_perm(process: &Process, ctx: &Context) -> Result<()> {
+    let credentials = ctx.posix_thread.credentials();
+    if credentials
+        .effective_capset()
+        .contains(CapSet::SYS_RESOURCE)
+    {
+        return Ok(());
+    }
+
+    let main_thread = process.main_thread();
+    let target_credentials = main_thread.as_posix_thread().unwrap().credentials();
+    let (uid, gid) = (credentials.ruid(), credentials.rgid());
+    let is_same_user = target_credentials.ruid() == uid
+        && target_credentials.euid() == uid
+        && target_credentials.suid() == uid;
+    let is_same_group = target_credentials.rgid() == gid
+        && target_credentials.egid() == gid
+        && target_credentials.sgid() == gid;
+    if is_same_user && is_same_group {
+        return Ok(());
+    }
+
+    return_errno_with_message!(
+        Errno::EPERM,
+        "the resource limits of the process cannot be accessed"
+    );
+}
+
+fn set_rlimit(
+    process: &Process,
+    resource: ResourceType,
+    new_raw: RawRLimit64,
+    ctx: &Context,
+) -> Result<()> {
+    if new_raw.cur > new_raw.max {
+        return_errno_with_message!(Errno::EINVAL, "the soft limit exceeds the hard limit");
+    }
+
+    let rlimit = process.resource_limits().get_rlimit(resource);
+    let can_raise_max = ctx
+        .posix_thread
+        .credentials()
+        .effective_capset()
+        .contains(CapSet::SYS_RESOURCE);
+    if new_raw.max > rlimit.get_max() && !can_raise_max {
+        return_errno_with_message!(Errno::EPERM, "the hard limit cannot be raised");
+    }
+    if matches!(resource, ResourceType::RLIMIT_NOFILE) && new_raw.max > nr_open() {
+        return_errno_with_message!(Errno::EPERM, "the file limit exceeds `nr_open`");
+    }
+
+    rlimit.set_cur_and_max(new_raw.cur, new_raw.max)
+}
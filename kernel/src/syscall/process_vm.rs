@@ -0,0 +1,174 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::ops::Range;
+
+use align_ext::AlignExt;
+use ostd::mm::{UFrame, UntypedMem};
+
+use super::SyscallReturn;
+use crate::{
+    prelude::*,
+    process::{check_ptrace_access, process_table, Pid, Process},
+    util::{read_user_io_vec_ranges, MultiRead, MultiWrite, VmReaderArray, VmWriterArray},
+    vm::perms::VmPerms,
+};
+
+/// The maximum number of I/O vectors.
+const UIO_MAXIOV: usize = 1024;
+
+pub fn sys_process_vm_readv(
+    pid: Pid,
+    local_iov_ptr: Vaddr,
+    local_iov_count: usize,
+    remote_iov_ptr: Vaddr,
+    remote_iov_count: usize,
+    flags: u64,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    debug!(
+        "pid = {}, local_iov_ptr = {:#x}, local_iov_count = {}, remote_iov_ptr = {:#x}, remote_iov_count = {}, flags = {:#x}",
+        pid, local_iov_ptr, local_iov_count, remote_iov_ptr, remote_iov_count, flags
+    );
+
+    let (process, remote_ranges) = prepare_remote(
+        pid,
+        local_iov_count,
+        remote_iov_ptr,
+        remote_iov_count,
+        flags,
+        ctx,
+    )?;
+
+    let user_space = ctx.user_space();
+    let mut local_writers =
+        VmWriterArray::from_user_io_vecs(&user_space, local_iov_ptr, local_iov_count)?;
+    let copied_len = copy_remote(&process, &remote_ranges, VmPerms::READ, |frame, range| {
+        let mut reader = frame.reader();
+        reader.skip(range.start).limit(range.len());
+        local_writers.write(&mut reader)
+    })?;
+
+    Ok(SyscallReturn::Return(copied_len as _))
+}
+
+pub fn sys_process_vm_writev(
+    pid: Pid,
+    local_iov_ptr: Vaddr,
+    local_iov_count: usize,
+    remote_iov_ptr: Vaddr,
+    remote_iov_count: usize,
+    flags: u64,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    debug!(
+        "pid = {}, local_iov_ptr = {:#x}, local_iov_count = {}, remote_iov_ptr = {:#x}, remote_iov_count = {}, flags = {:#x}",
+        pid, local_iov_ptr, local_iov_count, remote_iov_ptr, remote_iov_count, flags
+    );
+
+    let (process, remote_ranges) = prepare_remote(
+        pid,
+        local_iov_count,
+        remote_iov_ptr,
+        remote_iov_count,
+        flags,
+        ctx,
+    )?;
+
+    let user_space = ctx.user_space();
+    let mut local_readers =
+        VmReaderArray::from_user_io_vecs(&user_space, local_iov_ptr, local_iov_count)?;
+    let copied_len = copy_remote(&process, &remote_ranges, VmPerms::WRITE, |frame, range| {
+        let mut writer = frame.writer();
+        writer.skip(range.start).limit(range.len());
+        local_readers.read(&mut writer)
+    })?;
+
+    Ok(SyscallReturn::Return(copied_len as _))
+}
+
+/// Checks the arguments and the permission, and returns the remote process with the ranges of
+/// the remote buffers.
+fn prepare_remote(
+    pid: Pid,
+    local_iov_count: usize,
+    remote_iov_ptr: Vaddr,
+    remote_iov_count: usize,
+    flags: u64,
+    ctx: &Context,
+) -> Result<(Arc<Process>, Vec<Range<Vaddr>>)> {
+    if flags != 0 {
+        return_errno_with_message!(Errno::EINVAL, "invalid flags");
+    }
+    if local_iov_count > UIO_MAXIOV || remote_iov_count > UIO_MAXIOV {
+        return_errno_with_message!(Errno::EINVAL, "too many I/O vectors");
+    }
+
+    let remote_ranges =
+        read_user_io_vec_ranges(&ctx.user_space(), remote_iov_ptr, remote_iov_count)?;
+
+    let process = process_table::get_process(pid)
+        .ok_or_else(|| Error::with_message(Errno::ESRCH, "the process does not exist"))?;
+    // Like Linux, the caller must be allowed to attach to the process with `ptrace`.
+    check_ptrace_access(&process, ctx)?;
+
+    Ok((process, remote_ranges))
+}
+
+/// Copies between the remote buffers and the local buffers page by page, and returns the
+/// number of the bytes that are copied.
+///
+/// Each remote page is committed with the required permissions and pinned (see
+/// [`Vmar::pin_page`]), and `copy_page` copies with the range in the page. The copy stops at
+/// the first remote page that is not accessible, or when the local buffers are exhausted. An
+/// error is returned only if nothing is copied.
+///
+/// [`Vmar::pin_page`]: crate::vm::vmar::Vmar::pin_page
+fn copy_remote<F>(
+    process: &Process,
+    remote_ranges: &[Range<Vaddr>],
+    required_perms: VmPerms,
+    mut copy_page: F,
+) -> Result<usize>
+where
+    F: FnMut(&UFrame, Range<usize>) -> Result<usize>,
+{
+    let mut copied_len = 0;
+
+    for remote_range in remote_ranges {
+        let mut addr = remote_range.start;
+        while addr < remote_range.end {
+            let page_end = addr
+                .align_down(PAGE_SIZE)
+                .saturating_add(PAGE_SIZE)
+                .min(remote_range.end);
+            let offset = addr % PAGE_SIZE;
+            let len = page_end - addr;
+
+            // The VMAR is locked only when the page is pinned, so the local buffers can be
+            // accessed with page faults even if they are in the same process.
+            let frame = {
+                let root_vmar = process.vm().lock_root_vmar();
+                let Some(root_vmar) = root_vmar.try_get() else {
+                    return_errno_with_message!(Errno::ESRCH, "the process has exited");
+                };
+                root_vmar.pin_page(addr, required_perms)
+            };
+
+            let res = frame.and_then(|frame| copy_page(&frame, offset..offset + len));
+            let page_copied_len = match res {
+                Ok(page_copied_len) => page_copied_len,
+                Err(err) if copied_len == 0 => return Err(err),
+                Err(_) => return Ok(copied_len),
+            };
+            copied_len += page_copied_len;
+            if page_copied_len < len {
+                // The local buffers are exhausted.
+                return Ok(copied_len);
+            }
+
+            addr = page_end;
+        }
+    }
+
+    Ok(copied_len)
+}
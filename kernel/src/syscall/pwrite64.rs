@@ -38,3 +38,16 @@ pub fn sys_pwrite64(
     let write_len = file.write_at(offset as _, &mut reader)?;
     Ok(SyscallReturn::Return(write_len as _))
 }
+
+/// The `pwrite64` system call of 32-bit programs, whose offset is split into two registers.
+pub fn compat_sys_pwrite64(
+    fd: FileDesc,
+    user_buf_ptr: Vaddr,
+    user_buf_len: usize,
+    offset_low: u32,
+    offset_high: u32,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let offset = ((offset_high as u64) << 32 | offset_low as u64) as i64;
+    sys_pwrite64(fd, user_buf_ptr, user_buf_len, offset, ctx)
+}
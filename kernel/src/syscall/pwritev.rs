@@ -13,7 +13,7 @@ pub fn sys_writev(
     io_vec_count: usize,
     ctx: &Context,
 ) -> Result<SyscallReturn> {
-    let res = do_sys_writev(fd, io_vec_ptr, io_vec_count, ctx)?;
+    let res = do_sys_writev(fd, io_vec_ptr, io_vec_count, false, ctx)?;
     Ok(SyscallReturn::Return(res as _))
 }
 
@@ -24,7 +24,15 @@ pub fn sys_pwritev(
     offset: i64,
     ctx: &Context,
 ) -> Result<SyscallReturn> {
-    let res = do_sys_pwritev(fd, io_vec_ptr, io_vec_count, offset, RWFFlag::empty(), ctx)?;
+    let res = do_sys_pwritev(
+        fd,
+        io_vec_ptr,
+        io_vec_count,
+        offset,
+        RWFFlag::empty(),
+        false,
+        ctx,
+    )?;
     Ok(SyscallReturn::Return(res as _))
 }
 
@@ -41,19 +49,53 @@ pub fn sys_pwritev2(
         None => return_errno_with_message!(Errno::EINVAL, "invalid flags"),
     };
     let res = if offset == -1 {
-        do_sys_writev(fd, io_vec_ptr, io_vec_count, ctx)?
+        do_sys_writev(fd, io_vec_ptr, io_vec_count, false, ctx)?
     } else {
-        do_sys_pwritev(fd, io_vec_ptr, io_vec_count, offset, flags, ctx)?
+        do_sys_pwritev(fd, io_vec_ptr, io_vec_count, offset, flags, false, ctx)?
     };
     Ok(SyscallReturn::Return(res as _))
 }
 
+/// The `writev` system call of 32-bit programs.
+pub fn compat_sys_writev(
+    fd: FileDesc,
+    io_vec_ptr: Vaddr,
+    io_vec_count: usize,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let res = do_sys_writev(fd, io_vec_ptr, io_vec_count, true, ctx)?;
+    Ok(SyscallReturn::Return(res as _))
+}
+
+/// The `pwritev` system call of 32-bit programs, whose offset is split into two registers.
+pub fn compat_sys_pwritev(
+    fd: FileDesc,
+    io_vec_ptr: Vaddr,
+    io_vec_count: usize,
+    offset_low: u32,
+    offset_high: u32,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let offset = ((offset_high as u64) << 32 | offset_low as u64) as i64;
+    let res = do_sys_pwritev(
+        fd,
+        io_vec_ptr,
+        io_vec_count,
+        offset,
+        RWFFlag::empty(),
+        true,
+        ctx,
+    )?;
+    Ok(SyscallReturn::Return(res as _))
+}
+
 fn do_sys_pwritev(
     fd: FileDesc,
     io_vec_ptr: Vaddr,
     io_vec_count: usize,
     offset: i64,
     _flags: RWFFlag,
+    is_compat: bool,
     ctx: &Context,
 ) -> Result<usize> {
     // TODO: Implement flags support
@@ -78,7 +120,11 @@ fn do_sys_pwritev(
     let mut cur_offset = offset as usize;
 
     let user_space = ctx.user_space();
-    let mut reader_array = VmReaderArray::from_user_io_vecs(&user_space, io_vec_ptr, io_vec_count)?;
+    let mut reader_array = if is_compat {
+        VmReaderArray::from_compat_user_io_vecs(&user_space, io_vec_ptr, io_vec_count)?
+    } else {
+        VmReaderArray::from_user_io_vecs(&user_space, io_vec_ptr, io_vec_count)?
+    };
     for reader in reader_array.readers_mut() {
         if !reader.has_remain() {
             continue;
@@ -116,6 +162,7 @@ fn do_sys_writev(
     fd: FileDesc,
     io_vec_ptr: Vaddr,
     io_vec_count: usize,
+    is_compat: bool,
     ctx: &Context,
 ) -> Result<usize> {
     debug!(
@@ -129,7 +176,11 @@ fn do_sys_writev(
     let mut total_len = 0;
 
     let user_space = ctx.user_space();
-    let mut reader_array = VmReaderArray::from_user_io_vecs(&user_space, io_vec_ptr, io_vec_count)?;
+    let mut reader_array = if is_compat {
+        VmReaderArray::from_compat_user_io_vecs(&user_space, io_vec_ptr, io_vec_count)?
+    } else {
+        VmReaderArray::from_user_io_vecs(&user_space, io_vec_ptr, io_vec_count)?
+    };
     for reader in reader_array.readers_mut() {
         if !reader.has_remain() {
             continue;
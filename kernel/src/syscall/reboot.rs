@@ -0,0 +1,77 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use ostd::arch::qemu::{exit_qemu, QemuExitCode};
+
+use super::SyscallReturn;
+use crate::{prelude::*, process::credentials::capabilities::CapSet};
+
+pub fn sys_reboot(
+    magic1: u32,
+    magic2: u32,
+    cmd: u32,
+    arg: Vaddr,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    debug!(
+        "magic1 = {:#x}, magic2 = {:#x}, cmd = {:#x}, arg = {:#x}",
+        magic1, magic2, cmd, arg
+    );
+
+    if !ctx
+        .posix_thread
+        .credentials()
+        .effective_capset()
+        .contains(CapSet::SYS_BOOT)
+    {
+        return_errno_with_message!(Errno::EPERM, "`CAP_SYS_BOOT` is required");
+    }
+
+    if magic1 != LINUX_REBOOT_MAGIC1 || !LINUX_REBOOT_MAGIC2.contains(&magic2) {
+        return_errno_with_message!(Errno::EINVAL, "the magic numbers are invalid");
+    }
+
+    match cmd {
+        LINUX_REBOOT_CMD_RESTART => restart(),
+        LINUX_REBOOT_CMD_HALT | LINUX_REBOOT_CMD_POWER_OFF => {
+            // TODO: Sync the file systems and shut down the devices.
+            println!("Power down");
+            exit_qemu(QemuExitCode::Success)
+        }
+        // Ctrl-Alt-Del is not handled, so enabling or disabling it has no effect.
+        LINUX_REBOOT_CMD_CAD_ON | LINUX_REBOOT_CMD_CAD_OFF => Ok(SyscallReturn::Return(0)),
+        LINUX_REBOOT_CMD_KEXEC => kexec(),
+        _ => return_errno_with_message!(Errno::EINVAL, "the command is not supported"),
+    }
+}
+
+#[cfg(target_arch = "riscv64")]
+fn restart() -> Result<SyscallReturn> {
+    println!("Restarting system");
+    ostd::arch::reboot()
+}
+
+#[cfg(not(target_arch = "riscv64"))]
+fn restart() -> Result<SyscallReturn> {
+    return_errno_with_message!(Errno::EINVAL, "restarting is not supported");
+}
+
+#[cfg(target_arch = "riscv64")]
+fn kexec() -> Result<SyscallReturn> {
+    crate::kexec::execute_image()?;
+    unreachable!("the new kernel should have been started")
+}
+
+#[cfg(not(target_arch = "riscv64"))]
+fn kexec() -> Result<SyscallReturn> {
+    return_errno_with_message!(Errno::EINVAL, "kexec is not supported");
+}
+
+const LINUX_REBOOT_MAGIC1: u32 = 0xfee1_dead;
+const LINUX_REBOOT_MAGIC2: [u32; 4] = [672274793, 85072278, 369367448, 537993216];
+
+const LINUX_REBOOT_CMD_RESTART: u32 = 0x0123_4567;
+const LINUX_REBOOT_CMD_HALT: u32 = 0xcdef_0123;
+const LINUX_REBOOT_CMD_CAD_ON: u32 = 0x89ab_cdef;
+const LINUX_REBOOT_CMD_CAD_OFF: u32 = 0x0000_0000;
+const LINUX_REBOOT_CMD_POWER_OFF: u32 = 0x4321_fedc;
+const LINUX_REBOOT_CMD_KEXEC: u32 = 0x4558_4543;
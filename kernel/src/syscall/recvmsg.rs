@@ -14,7 +14,7 @@ pub fn sys_recvmsg(
     flags: i32,
     ctx: &Context,
 ) -> Result<SyscallReturn> {
-    let c_user_msghdr: CUserMsgHdr = ctx.user_space().read_val(user_msghdr_ptr)?;
+    let mut c_user_msghdr: CUserMsgHdr = ctx.user_space().read_val(user_msghdr_ptr)?;
     let flags = SendRecvFlags::from_bits_truncate(flags);
 
     debug!(
@@ -22,11 +22,11 @@ pub fn sys_recvmsg(
         sockfd, c_user_msghdr, flags
     );
 
-    let mut file_table = ctx.thread_local.file_table().borrow_mut();
-    let file = get_file_fast!(&mut file_table, sockfd);
-    let socket = file.as_socket_or_err()?;
-
     let (total_bytes, message_header) = {
+        let mut file_table = ctx.thread_local.file_table().borrow_mut();
+        let file = get_file_fast!(&mut file_table, sockfd);
+        let socket = file.as_socket_or_err()?;
+
         let user_space = ctx.user_space();
         let mut io_vec_writer = c_user_msghdr.copy_writer_array_from_user(&user_space)?;
         socket
@@ -42,9 +42,14 @@ pub fn sys_recvmsg(
         c_user_msghdr.write_socket_addr_to_user(addr)?;
     }
 
-    if c_user_msghdr.msg_control != 0 {
-        warn!("receiving control message is not supported");
-    }
+    // TODO: Set other flags (e.g., `MSG_TRUNC`)
+    c_user_msghdr.msg_flags = 0;
+    // The file table is no longer borrowed, so the files passed via `SCM_RIGHTS` can be
+    // installed.
+    c_user_msghdr.write_control_messages_to_user(message_header.control_messages(), flags, ctx)?;
+
+    ctx.user_space()
+        .write_val(user_msghdr_ptr, &c_user_msghdr)?;
 
     Ok(SyscallReturn::Return(total_bytes as _))
 }
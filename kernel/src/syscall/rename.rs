@@ -11,21 +11,34 @@ use crate::{
     syscall::constants::MAX_FILENAME_LEN,
 };
 
-pub fn sys_renameat(
+pub fn sys_renameat2(
     old_dirfd: FileDesc,
     old_path_addr: Vaddr,
     new_dirfd: FileDesc,
     new_path_addr: Vaddr,
+    flags: u32,
     ctx: &Context,
 ) -> Result<SyscallReturn> {
     let user_space = ctx.user_space();
     let old_path = user_space.read_cstring(old_path_addr, MAX_FILENAME_LEN)?;
     let new_path = user_space.read_cstring(new_path_addr, MAX_FILENAME_LEN)?;
+    let flags = RenameFlags::from_bits(flags)
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid flags"))?;
     debug!(
-        "old_dirfd = {}, old_path = {:?}, new_dirfd = {}, new_path = {:?}",
-        old_dirfd, old_path, new_dirfd, new_path
+        "old_dirfd = {}, old_path = {:?}, new_dirfd = {}, new_path = {:?}, flags = {:?}",
+        old_dirfd, old_path, new_dirfd, new_path, flags
     );
 
+    if flags.contains(RenameFlags::RENAME_NOREPLACE | RenameFlags::RENAME_EXCHANGE) {
+        return_errno_with_message!(
+            Errno::EINVAL,
+            "RENAME_NOREPLACE and RENAME_EXCHANGE are exclusive"
+        );
+    }
+    if flags.contains(RenameFlags::RENAME_WHITEOUT) {
+        return_errno_with_message!(Errno::EINVAL, "RENAME_WHITEOUT is not supported");
+    }
+
     let fs = ctx.posix_thread.fs().resolver().read();
 
     let (old_dir_dentry, old_name) = {
@@ -50,6 +63,28 @@ pub fn sys_renameat(
         fs.lookup_dir_and_base_name(&new_fs_path)?
     };
 
+    if flags.contains(RenameFlags::RENAME_NOREPLACE) && new_dir_dentry.lookup(&new_name).is_ok() {
+        return_errno_with_message!(Errno::EEXIST, "newpath exists");
+    }
+
+    if flags.contains(RenameFlags::RENAME_EXCHANGE) {
+        let new_dentry = new_dir_dentry.lookup(&new_name)?;
+        // Neither of the two can be an ancestor of the other.
+        let old_abs_path = old_dentry.abs_path();
+        let new_abs_path = new_dentry.abs_path();
+        if old_abs_path == new_abs_path {
+            return Ok(SyscallReturn::Return(0));
+        }
+        if is_ancestor_path(&old_abs_path, &new_abs_path)
+            || is_ancestor_path(&new_abs_path, &old_abs_path)
+        {
+            return_errno_with_message!(Errno::EINVAL, "one path is an ancestor of the other");
+        }
+
+        old_dir_dentry.exchange(&old_name, &new_dir_dentry, &new_name)?;
+        return Ok(SyscallReturn::Return(0));
+    }
+
     // Check abs_path
     let old_abs_path = old_dentry.abs_path();
     let new_abs_path = new_dir_dentry.abs_path() + "/" + &new_name;
@@ -69,6 +104,16 @@ pub fn sys_renameat(
     Ok(SyscallReturn::Return(0))
 }
 
+pub fn sys_renameat(
+    old_dirfd: FileDesc,
+    old_path_addr: Vaddr,
+    new_dirfd: FileDesc,
+    new_path_addr: Vaddr,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    self::sys_renameat2(old_dirfd, old_path_addr, new_dirfd, new_path_addr, 0, ctx)
+}
+
 pub fn sys_rename(
     old_path_addr: Vaddr,
     new_path_addr: Vaddr,
@@ -76,3 +121,23 @@ pub fn sys_rename(
 ) -> Result<SyscallReturn> {
     self::sys_renameat(AT_FDCWD, old_path_addr, AT_FDCWD, new_path_addr, ctx)
 }
+
+/// Returns whether `ancestor` is a proper ancestor of `path`, both of which are absolute.
+fn is_ancestor_path(ancestor: &str, path: &str) -> bool {
+    if ancestor == "/" {
+        return path != "/";
+    }
+    path.strip_prefix(ancestor)
+        .is_some_and(|remain| remain.starts_with('/'))
+}
+
+bitflags::bitflags! {
+    struct RenameFlags: u32 {
+        /// Do not overwrite the new path.
+        const RENAME_NOREPLACE = 1 << 0;
+        /// Exchange the old path and the new path atomically.
+        const RENAME_EXCHANGE = 1 << 1;
+        /// Leave a whiteout at the old path, which is used by overlay file systems.
+        const RENAME_WHITEOUT = 1 << 2;
+    }
+}
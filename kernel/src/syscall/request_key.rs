@@ -0,0 +1,52 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{
+    add_key::{read_description, read_key_type},
+    SyscallReturn,
+};
+use crate::{
+    prelude::*,
+    security::keys::{lookup_key, search_process_keyrings, KeyLookupFlags, KeyPerm, KeySerial},
+};
+
+pub fn sys_request_key(
+    type_addr: Vaddr,
+    description_addr: Vaddr,
+    callout_addr: Vaddr,
+    dest_keyring_serial: KeySerial,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let user_space = ctx.user_space();
+    let key_type = read_key_type(&user_space, type_addr)?;
+    let description = read_description(&user_space, description_addr)?;
+    debug!(
+        "type = {:?}, description = {:?}, callout = {:#x}, dest_keyring = {}",
+        key_type, description, callout_addr, dest_keyring_serial
+    );
+
+    let credentials = ctx.posix_thread.credentials();
+    let dest_keyring = if dest_keyring_serial != 0 {
+        let keyring = lookup_key(
+            ctx.posix_thread,
+            dest_keyring_serial,
+            KeyLookupFlags::CREATE,
+        )?;
+        keyring.check_permission(&credentials, KeyPerm::WRITE)?;
+        Some(keyring)
+    } else {
+        None
+    };
+
+    let Some(key) = search_process_keyrings(ctx.posix_thread, key_type, &description) else {
+        // TODO: Support constructing the key by calling out to `/sbin/request-key` with the
+        // callout information.
+        return_errno_with_message!(Errno::ENOKEY, "the key is not found");
+    };
+
+    if let Some(dest_keyring) = dest_keyring {
+        key.check_permission(&credentials, KeyPerm::LINK)?;
+        dest_keyring.key().link(key.key().clone())?;
+    }
+
+    Ok(SyscallReturn::Return(key.key().serial() as _))
+}
@@ -0,0 +1,119 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The `riscv_hwprobe` system call, which reports the ISA extensions and the implementation
+//! of the harts to the user space.
+//!
+//! Reference: <https://docs.kernel.org/arch/riscv/hwprobe.html>
+
+use ostd::{
+    arch::{hart, isa},
+    cpu::{cpu_features, CpuFeatures},
+};
+
+use super::SyscallReturn;
+use crate::prelude::*;
+
+/// A key-value pair, i.e., `struct riscv_hwprobe`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct RiscvHwprobe {
+    key: i64,
+    value: u64,
+}
+
+const RISCV_HWPROBE_KEY_MVENDORID: i64 = 0;
+const RISCV_HWPROBE_KEY_MARCHID: i64 = 1;
+const RISCV_HWPROBE_KEY_MIMPID: i64 = 2;
+const RISCV_HWPROBE_KEY_BASE_BEHAVIOR: i64 = 3;
+const RISCV_HWPROBE_KEY_IMA_EXT_0: i64 = 4;
+const RISCV_HWPROBE_KEY_CPUPERF_0: i64 = 5;
+const RISCV_HWPROBE_KEY_ZICBOZ_BLOCK_SIZE: i64 = 6;
+
+const RISCV_HWPROBE_BASE_BEHAVIOR_IMA: u64 = 1 << 0;
+
+const RISCV_HWPROBE_IMA_FD: u64 = 1 << 0;
+const RISCV_HWPROBE_IMA_C: u64 = 1 << 1;
+const RISCV_HWPROBE_EXT_ZKND: u64 = 1 << 11;
+const RISCV_HWPROBE_EXT_ZKNE: u64 = 1 << 12;
+const RISCV_HWPROBE_EXT_ZKNH: u64 = 1 << 13;
+
+const RISCV_HWPROBE_MISALIGNED_UNKNOWN: u64 = 0;
+
+pub fn sys_riscv_hwprobe(
+    pairs_addr: Vaddr,
+    pair_count: usize,
+    cpusetsize: usize,
+    cpus_addr: Vaddr,
+    flags: u32,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    debug!(
+        "pairs_addr = {:#x}, pair_count = {}, cpusetsize = {}, cpus_addr = {:#x}, flags = {:#x}",
+        pairs_addr, pair_count, cpusetsize, cpus_addr, flags
+    );
+
+    if flags != 0 {
+        return_errno_with_message!(Errno::EINVAL, "the hwprobe flags are not supported");
+    }
+
+    // All the harts are assumed to have the same features, so the values are the same for
+    // any set of CPUs.
+    let user_space = ctx.user_space();
+    for i in 0..pair_count {
+        let addr = pairs_addr + i * size_of::<RiscvHwprobe>();
+        let mut pair = user_space.read_val::<RiscvHwprobe>(addr)?;
+        match probe(pair.key) {
+            Some(value) => pair.value = value,
+            None => {
+                // The unknown keys are reported by being set to -1, as Linux does.
+                pair.key = -1;
+                pair.value = 0;
+            }
+        }
+        user_space.write_val(addr, &pair)?;
+    }
+
+    Ok(SyscallReturn::Return(0))
+}
+
+fn probe(key: i64) -> Option<u64> {
+    let has_letter = |letter: u8| isa::hwcap() & (1 << (letter - b'a')) != 0;
+
+    let value = match key {
+        RISCV_HWPROBE_KEY_MVENDORID => hart::machine_ids().mvendorid as u64,
+        RISCV_HWPROBE_KEY_MARCHID => hart::machine_ids().marchid as u64,
+        RISCV_HWPROBE_KEY_MIMPID => hart::machine_ids().mimpid as u64,
+        RISCV_HWPROBE_KEY_BASE_BEHAVIOR => {
+            if [b'i', b'm', b'a'].into_iter().all(has_letter) {
+                RISCV_HWPROBE_BASE_BEHAVIOR_IMA
+            } else {
+                0
+            }
+        }
+        RISCV_HWPROBE_KEY_IMA_EXT_0 => {
+            let features = cpu_features();
+            let mut value = 0;
+            if has_letter(b'f') && has_letter(b'd') {
+                value |= RISCV_HWPROBE_IMA_FD;
+            }
+            if has_letter(b'c') {
+                value |= RISCV_HWPROBE_IMA_C;
+            }
+            if features.contains(CpuFeatures::AES_DEC) {
+                value |= RISCV_HWPROBE_EXT_ZKND;
+            }
+            if features.contains(CpuFeatures::AES_ENC) {
+                value |= RISCV_HWPROBE_EXT_ZKNE;
+            }
+            if features.contains(CpuFeatures::SHA2) {
+                value |= RISCV_HWPROBE_EXT_ZKNH;
+            }
+            value
+        }
+        RISCV_HWPROBE_KEY_CPUPERF_0 => RISCV_HWPROBE_MISALIGNED_UNKNOWN,
+        // The Zicboz extension is not reported, so there is no block size.
+        RISCV_HWPROBE_KEY_ZICBOZ_BLOCK_SIZE => 0,
+        _ => return None,
+    };
+    Some(value)
+}
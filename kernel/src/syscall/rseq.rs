@@ -0,0 +1,74 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use ostd::mm::MAX_USERSPACE_VADDR;
+
+use super::SyscallReturn;
+use crate::{prelude::*, process::posix_thread::RseqArea};
+
+pub fn sys_rseq(
+    rseq_addr: Vaddr,
+    rseq_len: u32,
+    flags: i32,
+    sig: u32,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    debug!(
+        "rseq_addr = {:#x}, rseq_len = {}, flags = {}, sig = {:#x}",
+        rseq_addr, rseq_len, flags, sig
+    );
+
+    let rseq = ctx.thread_local.rseq();
+    let area = RseqArea {
+        addr: rseq_addr,
+        len: rseq_len,
+        sig,
+    };
+
+    match flags {
+        0 => {}
+        RSEQ_FLAG_UNREGISTER => {
+            let Some(registered) = rseq.area() else {
+                return_errno_with_message!(Errno::EINVAL, "no rseq area is registered");
+            };
+            if registered.addr != area.addr || registered.len != area.len {
+                return_errno_with_message!(Errno::EINVAL, "the rseq area is not registered");
+            }
+            if registered.sig != area.sig {
+                return_errno_with_message!(Errno::EPERM, "the rseq signature mismatches");
+            }
+            rseq.unregister(ctx)?;
+            return Ok(SyscallReturn::Return(0));
+        }
+        _ => return_errno_with_message!(Errno::EINVAL, "unknown rseq flags"),
+    }
+
+    if let Some(registered) = rseq.area() {
+        if registered.addr != area.addr || registered.len != area.len {
+            return_errno_with_message!(Errno::EINVAL, "another rseq area is registered");
+        }
+        if registered.sig != area.sig {
+            return_errno_with_message!(Errno::EPERM, "the rseq signature mismatches");
+        }
+        return_errno_with_message!(Errno::EBUSY, "the rseq area is already registered");
+    }
+
+    // The original `struct rseq` is of 32 bytes. The longer areas contain the extended fields,
+    // which are never accessed here.
+    if (rseq_len as usize) < ORIG_RSEQ_SIZE || rseq_addr % RSEQ_ALIGN != 0 {
+        return_errno_with_message!(Errno::EINVAL, "invalid rseq area");
+    }
+    if rseq_addr
+        .checked_add(rseq_len as usize)
+        .is_none_or(|end| end > MAX_USERSPACE_VADDR)
+    {
+        return_errno_with_message!(Errno::EFAULT, "the rseq area is not in the user space");
+    }
+
+    rseq.register(area);
+    Ok(SyscallReturn::Return(0))
+}
+
+const RSEQ_FLAG_UNREGISTER: i32 = 1 << 0;
+
+const ORIG_RSEQ_SIZE: usize = 32;
+const RSEQ_ALIGN: usize = 32;
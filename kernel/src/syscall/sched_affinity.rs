@@ -5,7 +5,14 @@ use core::{cmp, mem};
 use ostd::cpu::{num_cpus, CpuId, CpuSet};
 
 use super::SyscallReturn;
-use crate::{prelude::*, process::posix_thread::thread_table, thread::Tid};
+use crate::{
+    prelude::*,
+    process::{
+        cgroup::cpuset::restrict_affinity,
+        posix_thread::{thread_table, AsPosixThread},
+    },
+    thread::{Thread, Tid},
+};
 
 pub fn sys_sched_getaffinity(
     tid: Tid,
@@ -38,12 +45,18 @@ pub fn sys_sched_setaffinity(
 ) -> Result<SyscallReturn> {
     let user_cpu_set = read_cpu_set_from(ctx.user_space(), cpuset_size, cpu_set_ptr)?;
 
+    // The CPUs are restricted to the ones of the cpuset.
+    let set_affinity = |thread: &Thread| -> Result<()> {
+        let process = thread.as_posix_thread().unwrap().process();
+        let cpu_set = restrict_affinity(&process.cgroup(), &user_cpu_set)?;
+        thread.atomic_cpu_affinity().store(&cpu_set);
+        Ok(())
+    };
+
     match tid {
-        0 => ctx.thread.atomic_cpu_affinity().store(&user_cpu_set),
+        0 => set_affinity(ctx.thread)?,
         _ => match thread_table::get_thread(tid) {
-            Some(thread) => {
-                thread.atomic_cpu_affinity().store(&user_cpu_set);
-            }
+            Some(thread) => set_affinity(&thread)?,
             None => return Err(Error::with_message(Errno::ESRCH, "thread does not exist")),
         },
     }
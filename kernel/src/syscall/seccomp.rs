@@ -0,0 +1,115 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::SyscallReturn;
+use crate::{
+    bpf::cbpf::{SockFilter, BPF_MAXINSNS},
+    prelude::*,
+    process::credentials::capabilities::CapSet,
+    security::seccomp::{is_action_available, SeccompFilter, SeccompMode},
+};
+
+pub fn sys_seccomp(
+    operation: u32,
+    flags: u32,
+    args_addr: Vaddr,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    debug!(
+        "operation = {}, flags = {:#x}, args_addr = {:#x}",
+        operation, flags, args_addr
+    );
+
+    match operation {
+        SECCOMP_SET_MODE_STRICT => {
+            if flags != 0 || args_addr != 0 {
+                return_errno_with_message!(Errno::EINVAL, "invalid arguments of the strict mode");
+            }
+            set_mode_strict(ctx)?;
+        }
+        SECCOMP_SET_MODE_FILTER => set_mode_filter(flags, args_addr, ctx)?,
+        SECCOMP_GET_ACTION_AVAIL => {
+            if flags != 0 {
+                return_errno_with_message!(Errno::EINVAL, "invalid flags");
+            }
+            let action = ctx.user_space().read_val::<u32>(args_addr)?;
+            if !is_action_available(action) {
+                return_errno_with_message!(Errno::EOPNOTSUPP, "unknown seccomp action");
+            }
+        }
+        _ => return_errno_with_message!(Errno::EINVAL, "unsupported seccomp operation"),
+    }
+
+    Ok(SyscallReturn::Return(0))
+}
+
+/// Enters the strict mode, which is also used by `prctl(PR_SET_SECCOMP)`.
+pub(super) fn set_mode_strict(ctx: &Context) -> Result<()> {
+    let credentials = ctx.posix_thread.credentials_mut();
+    match credentials.seccomp_mode() {
+        SeccompMode::Disabled | SeccompMode::Strict => (),
+        SeccompMode::Filter(_) => {
+            return_errno_with_message!(Errno::EINVAL, "the seccomp mode cannot be changed")
+        }
+    }
+    credentials.set_seccomp_mode(SeccompMode::Strict);
+    Ok(())
+}
+
+/// Installs a filter, which is also used by `prctl(PR_SET_SECCOMP)`.
+pub(super) fn set_mode_filter(flags: u32, prog_addr: Vaddr, ctx: &Context) -> Result<()> {
+    if flags & !(SECCOMP_FILTER_FLAG_LOG | SECCOMP_FILTER_FLAG_SPEC_ALLOW) != 0 {
+        return_errno_with_message!(Errno::EINVAL, "unsupported seccomp filter flags");
+    }
+
+    // A thread that is not privileged cannot pass its restrictions to a privileged program,
+    // which may be abused to confuse the program.
+    let credentials = ctx.posix_thread.credentials_mut();
+    if !credentials.no_new_privs() && !credentials.effective_capset().contains(CapSet::SYS_ADMIN) {
+        return_errno_with_message!(
+            Errno::EACCES,
+            "no_new_privs must be set to install a seccomp filter"
+        );
+    }
+
+    let prev = match credentials.seccomp_mode() {
+        SeccompMode::Disabled => None,
+        SeccompMode::Strict => {
+            return_errno_with_message!(Errno::EINVAL, "the seccomp mode cannot be changed")
+        }
+        SeccompMode::Filter(filter) => Some(filter),
+    };
+
+    let user_space = ctx.user_space();
+    let fprog = user_space.read_val::<SockFprog>(prog_addr)?;
+    let len = fprog.len as usize;
+    if len == 0 || len > BPF_MAXINSNS {
+        return_errno_with_message!(Errno::EINVAL, "invalid length of the seccomp filter");
+    }
+    let filter = (0..len)
+        .map(|i| {
+            user_space.read_val::<SockFilter>(fprog.filter as Vaddr + i * size_of::<SockFilter>())
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let log = flags & SECCOMP_FILTER_FLAG_LOG != 0;
+    let filter = SeccompFilter::new(&filter, prev, log)?;
+    credentials.set_seccomp_mode(SeccompMode::Filter(Arc::new(filter)));
+
+    Ok(())
+}
+
+const SECCOMP_SET_MODE_STRICT: u32 = 0;
+const SECCOMP_SET_MODE_FILTER: u32 = 1;
+const SECCOMP_GET_ACTION_AVAIL: u32 = 2;
+
+const SECCOMP_FILTER_FLAG_LOG: u32 = 1 << 1;
+const SECCOMP_FILTER_FLAG_SPEC_ALLOW: u32 = 1 << 2;
+
+/// A classic BPF program, i.e., `struct sock_fprog`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct SockFprog {
+    len: u16,
+    _pad: [u8; 6],
+    filter: u64,
+}
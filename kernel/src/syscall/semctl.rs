@@ -1,17 +1,19 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use aster_rights::ReadOp;
+
 use super::SyscallReturn;
 use crate::{
     ipc::{
+        ids::IpcObject,
         semaphore::system_v::{
             sem::Semaphore,
-            sem_set::{check_sem, sem_sets, sem_sets_mut, SemaphoreSet},
-            PermissionMode,
+            sem_set::{sem_sets, SemaphoreSet},
         },
-        IpcControlCmd,
+        CIpcPerm, IpcControlCmd, PermissionMode, IPC_64,
     },
     prelude::*,
-    process::Pid,
+    process::{Credentials, Gid, Pid, Uid},
 };
 
 pub fn sys_semctl(
@@ -25,27 +27,45 @@ pub fn sys_semctl(
         return_errno!(Errno::EINVAL)
     }
 
-    let cmd = IpcControlCmd::try_from(cmd)?;
+    let cmd = IpcControlCmd::try_from(cmd & !IPC_64)?;
     debug!(
         "[sys_semctl] semid = {}, semnum = {}, cmd = {:?}, arg = {:x}",
         semid, semnum, cmd, arg
     );
 
+    let credentials = ctx.posix_thread.credentials();
+
     match cmd {
         IpcControlCmd::IPC_RMID => {
-            let mut sem_sets_mut = sem_sets_mut();
-            let sem_set = sem_sets_mut.get(&semid).ok_or(Error::new(Errno::EINVAL))?;
+            sem_sets().remove(semid, |sem_set| {
+                sem_set.permission().check_owner(&credentials)
+            })?;
+        }
+        IpcControlCmd::IPC_SET => {
+            let sem_ds = ctx.user_space().read_val::<CSemidDs>(arg)?;
 
-            let euid = ctx.posix_thread.credentials().euid();
+            let sem_set = sem_sets().get(semid)?;
             let permission = sem_set.permission();
-            let can_removed = (euid == permission.uid()) || (euid == permission.cuid());
-            if !can_removed {
-                return_errno!(Errno::EPERM);
-            }
+            permission.check_owner(&credentials)?;
+            permission.set(
+                Uid::new(sem_ds.sem_perm.uid),
+                Gid::new(sem_ds.sem_perm.gid),
+                sem_ds.sem_perm.mode as u16,
+            );
+            sem_set.update_ctime();
+        }
+        IpcControlCmd::IPC_STAT => {
+            let sem_ds = check_and_ctl(semid, PermissionMode::READ, &credentials, |sem_set| {
+                Ok(CSemidDs {
+                    sem_perm: sem_set.permission().to_c_perm(),
+                    sem_otime: sem_set.sem_otime().as_secs() as i64,
+                    sem_ctime: sem_set.sem_ctime().as_secs() as i64,
+                    sem_nsems: sem_set.nsems() as u64,
+                    ..CSemidDs::new_zeroed()
+                })
+            })?;
 
-            sem_sets_mut
-                .remove(&semid)
-                .ok_or(Error::new(Errno::EINVAL))?;
+            ctx.user_space().write_val(arg, &sem_ds)?;
         }
         IpcControlCmd::SEM_SETVAL => {
             // In setval, arg is parse as i32
@@ -54,15 +74,37 @@ pub fn sys_semctl(
                 return_errno!(Errno::ERANGE);
             }
 
-            check_and_ctl(semid, PermissionMode::ALTER, |sem_set| {
+            check_and_ctl(semid, PermissionMode::ALTER, &credentials, |sem_set| {
                 sem_set.setval(semnum as usize, val, ctx.process.pid())
             })?;
         }
+        IpcControlCmd::SEM_SETALL => {
+            check_and_ctl(semid, PermissionMode::ALTER, &credentials, |sem_set| {
+                let mut buf = vec![0u8; sem_set.nsems() * size_of::<u16>()];
+                ctx.user_space()
+                    .read_bytes(arg, &mut VmWriter::from(buf.as_mut_slice()))?;
+                let vals: Vec<u16> = buf
+                    .chunks_exact(size_of::<u16>())
+                    .map(|bytes| u16::from_ne_bytes([bytes[0], bytes[1]]))
+                    .collect();
+
+                sem_set.setall(&vals, ctx.process.pid())
+            })?;
+        }
+        IpcControlCmd::SEM_GETALL => {
+            let vals = check_and_ctl(semid, PermissionMode::READ, &credentials, |sem_set| {
+                Ok(sem_set.getall())
+            })?;
+
+            let buf: Vec<u8> = vals.iter().flat_map(|val| val.to_ne_bytes()).collect();
+            ctx.user_space()
+                .write_bytes(arg, &mut VmReader::from(buf.as_slice()))?;
+        }
         IpcControlCmd::SEM_GETVAL => {
             fn sem_val(sem: &Semaphore) -> i32 {
                 sem.val()
             }
-            let val: i32 = check_and_ctl(semid, PermissionMode::READ, |sem_set| {
+            let val: i32 = check_and_ctl(semid, PermissionMode::READ, &credentials, |sem_set| {
                 sem_set.get(semnum as usize, &sem_val)
             })?;
 
@@ -72,38 +114,57 @@ pub fn sys_semctl(
             fn sem_pid(sem: &Semaphore) -> Pid {
                 sem.latest_modified_pid()
             }
-            let pid: Pid = check_and_ctl(semid, PermissionMode::READ, |sem_set| {
+            let pid: Pid = check_and_ctl(semid, PermissionMode::READ, &credentials, |sem_set| {
                 sem_set.get(semnum as usize, &sem_pid)
             })?;
 
             return Ok(SyscallReturn::Return(pid as isize));
         }
         IpcControlCmd::SEM_GETZCNT => {
-            let cnt: usize = check_and_ctl(semid, PermissionMode::READ, |sem_set| {
+            let cnt: usize = check_and_ctl(semid, PermissionMode::READ, &credentials, |sem_set| {
                 Ok(sem_set.pending_const_count(semnum as u16))
             })?;
 
             return Ok(SyscallReturn::Return(cnt as isize));
         }
         IpcControlCmd::SEM_GETNCNT => {
-            let cnt: usize = check_and_ctl(semid, PermissionMode::READ, |sem_set| {
+            let cnt: usize = check_and_ctl(semid, PermissionMode::READ, &credentials, |sem_set| {
                 Ok(sem_set.pending_alter_count(semnum as u16))
             })?;
 
             return Ok(SyscallReturn::Return(cnt as isize));
         }
-        _ => todo!("Need to support {:?} in SYS_SEMCTL", cmd),
     }
 
     Ok(SyscallReturn::Return(0))
 }
 
-fn check_and_ctl<T, F>(semid: i32, permission: PermissionMode, ctl_func: F) -> Result<T>
+fn check_and_ctl<T, F>(
+    semid: i32,
+    permission: PermissionMode,
+    credentials: &Credentials<ReadOp>,
+    ctl_func: F,
+) -> Result<T>
 where
     F: FnOnce(&SemaphoreSet) -> Result<T>,
 {
-    check_sem(semid, None, permission)?;
-    let sem_sets = sem_sets();
-    let sem_set = sem_sets.get(&semid).ok_or(Error::new(Errno::EINVAL))?;
-    ctl_func.call_once((sem_set,))
+    let sem_set = sem_sets().get(semid)?;
+    sem_set.permission().check_access(credentials, permission)?;
+    ctl_func(&sem_set)
+}
+
+/// The data structure of a semaphore set (`struct semid64_ds` in Linux).
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct CSemidDs {
+    sem_perm: CIpcPerm,
+    sem_otime: i64,
+    #[cfg(target_arch = "x86_64")]
+    _unused1: u64,
+    sem_ctime: i64,
+    #[cfg(target_arch = "x86_64")]
+    _unused2: u64,
+    sem_nsems: u64,
+    _unused3: u64,
+    _unused4: u64,
 }
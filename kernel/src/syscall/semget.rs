@@ -3,11 +3,9 @@
 use super::SyscallReturn;
 use crate::{
     ipc::{
-        semaphore::system_v::{
-            sem_set::{check_sem, create_sem_set, create_sem_set_with_id, SEMMSL},
-            PermissionMode,
-        },
-        IpcFlags,
+        ids::IpcObject,
+        semaphore::system_v::sem_set::{sem_sets, SemaphoreSet, SEMMSL},
+        IpcFlags, PermissionMode,
     },
     prelude::*,
 };
@@ -30,40 +28,26 @@ pub fn sys_semget(key: i32, nsems: i32, semflags: i32, ctx: &Context) -> Result<
         key, nsems, semflags
     );
 
-    // Create a new semaphore set directly
-    const IPC_NEW: i32 = 0;
-    if key == IPC_NEW {
-        if nsems == 0 {
-            return_errno!(Errno::EINVAL);
-        }
-        return Ok(SyscallReturn::Return(
-            create_sem_set(nsems, mode, credentials)? as isize,
-        ));
-    }
-
     // Get a semaphore set, and create if necessary
-    match check_sem(
+    let id = sem_sets().get_or_create(
         key,
-        Some(nsems),
-        PermissionMode::ALTER | PermissionMode::READ,
-    ) {
-        Ok(_) => {
-            if flags.contains(IpcFlags::IPC_CREAT | IpcFlags::IPC_EXCL) {
-                return_errno!(Errno::EEXIST);
-            }
-        }
-        Err(err) => {
-            let need_create = err.error() == Errno::ENOENT && flags.contains(IpcFlags::IPC_CREAT);
-            if !need_create {
-                return Err(err);
+        flags,
+        |sem_set| {
+            sem_set
+                .permission()
+                .check_access(&credentials, PermissionMode::from_requested_mode(mode))?;
+            if nsems > sem_set.nsems() {
+                return_errno!(Errno::EINVAL);
             }
+            Ok(())
+        },
+        |_| {
             if nsems == 0 {
                 return_errno!(Errno::EINVAL);
             }
+            SemaphoreSet::new(key, nsems, mode, &credentials)
+        },
+    )?;
 
-            create_sem_set_with_id(key, nsems, mode, credentials)?
-        }
-    };
-
-    Ok(SyscallReturn::Return(key as isize))
+    Ok(SyscallReturn::Return(id as isize))
 }
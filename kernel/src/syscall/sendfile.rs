@@ -1,8 +1,14 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use super::SyscallReturn;
+use super::{
+    splice::{
+        check_readable, check_writable, copy_between_files, get_file_pair, read_from_file,
+        MAX_COUNT,
+    },
+    SyscallReturn,
+};
 use crate::{
-    fs::file_table::{FileDesc, WithFileTable},
+    fs::{file_table::FileDesc, pipe::PipeWriter, utils::StatusFlags},
     prelude::*,
 };
 
@@ -36,83 +42,33 @@ pub fn sys_sendfile(
         count as usize
     };
 
-    let (out_file, in_file) = ctx
-        .thread_local
-        .file_table()
-        .borrow_mut()
-        .read_with(|inner| {
-            let out_file = inner.get_file(out_fd)?.clone();
-            // FIXME: the in_file must support mmap-like operations (i.e., it cannot be a socket).
-            let in_file = inner.get_file(in_fd)?.clone();
-            Ok::<_, Error>((out_file, in_file))
-        })?;
+    let (in_file, out_file) = get_file_pair(in_fd, out_fd, ctx)?;
+    check_readable(&in_file)?;
+    check_writable(&out_file)?;
 
     // sendfile can send at most `MAX_COUNT` bytes
-    const MAX_COUNT: usize = 0x7fff_f000;
     if count > MAX_COUNT {
         count = MAX_COUNT;
     }
 
-    const BUFFER_SIZE: usize = PAGE_SIZE;
-    let mut buffer = vec![0u8; BUFFER_SIZE].into_boxed_slice();
-    let mut total_len = 0;
+    // The offset decides how to read from `in_file`.
+    // If offset is `Some(_)`, the data will be read from the given offset,
+    // and after reading, the file offset of `in_file` will remain unchanged.
+    // If offset is `None`, the data will be read from the file offset,
+    // and the file offset of `in_file` is adjusted
+    // to reflect the number of bytes read from `in_file`.
     let mut offset = offset.map(|offset| offset as usize);
 
-    while total_len < count {
-        // The offset decides how to read from `in_file`.
-        // If offset is `Some(_)`, the data will be read from the given offset,
-        // and after reading, the file offset of `in_file` will remain unchanged.
-        // If offset is `None`, the data will be read from the file offset,
-        // and the file offset of `in_file` is adjusted
-        // to reflect the number of bytes read from `in_file`.
-        let max_readlen = buffer.len().min(count - total_len);
-
-        // Read from `in_file`
-        let read_res = if let Some(offset) = offset.as_mut() {
-            let res = in_file.read_bytes_at(*offset, &mut buffer[..max_readlen]);
-            if let Ok(len) = res.as_ref() {
-                *offset += *len;
-            }
-            res
-        } else {
-            in_file.read_bytes(&mut buffer[..max_readlen])
-        };
-
-        let read_len = match read_res {
-            Ok(len) => len,
-            Err(e) => {
-                if total_len > 0 {
-                    warn!("error occurs when trying to read file: {:?}", e);
-                    break;
-                }
-                return Err(e);
-            }
-        };
-
-        if read_len == 0 {
-            break;
-        }
-
-        // Note: `sendfile` allows sending partial data,
-        // so short reads and short writes are all acceptable
-        let write_res = out_file.write_bytes(&buffer[..read_len]);
-
-        match write_res {
-            Ok(len) => {
-                total_len += len;
-                if len < BUFFER_SIZE {
-                    break;
-                }
-            }
-            Err(e) => {
-                if total_len > 0 {
-                    warn!("error occurs when trying to write file: {:?}", e);
-                    break;
-                }
-                return Err(e);
-            }
-        }
-    }
+    // The data are moved into the pipe buffer or written to `out_file` from the page
+    // cache of `in_file` directly, without copying them to a temporary buffer.
+    let total_len = if let Some(out_pipe) = out_file.downcast_ref::<PipeWriter>() {
+        let is_nonblocking = out_pipe.status_flags().contains(StatusFlags::O_NONBLOCK);
+        out_pipe.splice_from(count, is_nonblocking, |writer| {
+            read_from_file(&in_file, offset.as_mut(), writer)
+        })?
+    } else {
+        copy_between_files(&in_file, offset.as_mut(), &out_file, None, count)?
+    };
 
     if let Some(offset) = offset {
         ctx.user_space().write_val(offset_ptr, &(offset as isize))?;
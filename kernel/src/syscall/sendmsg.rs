@@ -22,6 +22,10 @@ pub fn sys_sendmsg(
         sockfd, c_user_msghdr, flags
     );
 
+    // The control messages may refer to files in the file table, so they must be read before
+    // the file table is borrowed below.
+    let control_messages = c_user_msghdr.read_control_messages_from_user(ctx)?;
+
     let mut file_table = ctx.thread_local.file_table().borrow_mut();
     let file = get_file_fast!(&mut file_table, sockfd);
     let socket = file.as_socket_or_err()?;
@@ -31,15 +35,7 @@ pub fn sys_sendmsg(
         let addr = c_user_msghdr.read_socket_addr_from_user()?;
         let io_vec_reader = c_user_msghdr.copy_reader_array_from_user(&user_space)?;
 
-        let control_message = {
-            if c_user_msghdr.msg_control != 0 {
-                // TODO: support sending control message
-                warn!("control message is not supported now");
-            }
-            None
-        };
-
-        (io_vec_reader, MessageHeader::new(addr, control_message))
+        (io_vec_reader, MessageHeader::new(addr, control_messages))
     };
 
     let total_bytes = socket
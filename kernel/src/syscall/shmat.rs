@@ -0,0 +1,66 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use align_ext::AlignExt;
+
+use super::SyscallReturn;
+use crate::{
+    ipc::{
+        ids::IpcObject,
+        shm::{shm_segments, ShmFlags, SHMLBA},
+        PermissionMode,
+    },
+    prelude::*,
+    vm::perms::VmPerms,
+};
+
+pub fn sys_shmat(shmid: i32, shmaddr: Vaddr, shmflg: i32, ctx: &Context) -> Result<SyscallReturn> {
+    let flags = ShmFlags::from_bits_truncate(shmflg as u32);
+    debug!(
+        "[sys_shmat] shmid = {}, shmaddr = {:#x}, flags = {:?}",
+        shmid, shmaddr, flags
+    );
+
+    let mut addr = shmaddr;
+    if addr % SHMLBA != 0 {
+        if !flags.contains(ShmFlags::SHM_RND) {
+            return_errno_with_message!(Errno::EINVAL, "the address is not aligned");
+        }
+        addr = addr.align_down(SHMLBA);
+    }
+    if addr == 0 && flags.contains(ShmFlags::SHM_REMAP) {
+        return_errno_with_message!(Errno::EINVAL, "`SHM_REMAP` requires an address");
+    }
+
+    let (mut perms, required_perm) = if flags.contains(ShmFlags::SHM_RDONLY) {
+        (VmPerms::READ, PermissionMode::READ)
+    } else {
+        (
+            VmPerms::READ | VmPerms::WRITE,
+            PermissionMode::READ | PermissionMode::WRITE,
+        )
+    };
+    if flags.contains(ShmFlags::SHM_EXEC) {
+        perms |= VmPerms::EXEC;
+    }
+
+    let segment = shm_segments().get(shmid)?;
+    segment
+        .permission()
+        .check_access(&ctx.posix_thread.credentials(), required_perm)?;
+
+    let user_space = ctx.user_space();
+    let root_vmar = user_space.root_vmar();
+    let mut options = root_vmar
+        .new_map(segment.size().align_up(PAGE_SIZE), perms)?
+        .vmo(segment.vmo().dup()?)
+        .is_shared(true);
+    if addr != 0 {
+        options = options
+            .offset(addr)
+            .can_overwrite(flags.contains(ShmFlags::SHM_REMAP));
+    }
+    let map_addr = options.build()?;
+
+    segment.on_attach(ctx.process.pid());
+    Ok(SyscallReturn::Return(map_addr as _))
+}
@@ -0,0 +1,84 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::SyscallReturn;
+use crate::{
+    ipc::{
+        ids::IpcObject,
+        shm::{remove_detached_segments, shm_segments},
+        CIpcPerm, IpcControlCmd, PermissionMode, IPC_64,
+    },
+    prelude::*,
+    process::{Gid, Uid},
+};
+
+pub fn sys_shmctl(shmid: i32, cmd: i32, buf: Vaddr, ctx: &Context) -> Result<SyscallReturn> {
+    let cmd = IpcControlCmd::try_from(cmd & !IPC_64)?;
+    debug!(
+        "[sys_shmctl] shmid = {}, cmd = {:?}, buf = {:#x}",
+        shmid, cmd, buf
+    );
+
+    remove_detached_segments();
+
+    let credentials = ctx.posix_thread.credentials();
+    let segment = shm_segments().get(shmid)?;
+    let permission = segment.permission();
+
+    match cmd {
+        IpcControlCmd::IPC_RMID => {
+            permission.check_owner(&credentials)?;
+            // Like Linux, the segment is destroyed after the last detach.
+            segment.destroy();
+            drop(segment);
+            remove_detached_segments();
+        }
+        IpcControlCmd::IPC_SET => {
+            let shm_ds = ctx.user_space().read_val::<CShmidDs>(buf)?;
+
+            permission.check_owner(&credentials)?;
+            permission.set(
+                Uid::new(shm_ds.shm_perm.uid),
+                Gid::new(shm_ds.shm_perm.gid),
+                shm_ds.shm_perm.mode as u16,
+            );
+            segment.update_ctime();
+        }
+        IpcControlCmd::IPC_STAT => {
+            permission.check_access(&credentials, PermissionMode::READ)?;
+
+            let shm_ds = CShmidDs {
+                shm_perm: CIpcPerm {
+                    mode: segment.c_mode(),
+                    ..permission.to_c_perm()
+                },
+                shm_segsz: segment.size() as u64,
+                shm_atime: segment.atime().as_secs() as i64,
+                shm_dtime: segment.dtime().as_secs() as i64,
+                shm_ctime: segment.ctime().as_secs() as i64,
+                shm_cpid: segment.creator_pid(),
+                shm_lpid: segment.latest_pid(),
+                shm_nattch: segment.nattch() as u64,
+                ..CShmidDs::new_zeroed()
+            };
+            ctx.user_space().write_val(buf, &shm_ds)?;
+        }
+        _ => return_errno_with_message!(Errno::EINVAL, "the command is not supported"),
+    }
+
+    Ok(SyscallReturn::Return(0))
+}
+
+/// The data structure of a shared memory segment (`struct shmid64_ds` in Linux).
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct CShmidDs {
+    shm_perm: CIpcPerm,
+    shm_segsz: u64,
+    shm_atime: i64,
+    shm_dtime: i64,
+    shm_ctime: i64,
+    shm_cpid: u32,
+    shm_lpid: u32,
+    shm_nattch: u64,
+    _unused: [u64; 2],
+}
@@ -0,0 +1,37 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use align_ext::AlignExt;
+
+use super::SyscallReturn;
+use crate::{
+    ipc::shm::{remove_detached_segments, shm_segments},
+    prelude::*,
+};
+
+pub fn sys_shmdt(shmaddr: Vaddr, ctx: &Context) -> Result<SyscallReturn> {
+    debug!("[sys_shmdt] shmaddr = {:#x}", shmaddr);
+
+    if shmaddr % PAGE_SIZE != 0 {
+        return_errno_with_message!(Errno::EINVAL, "the address is not aligned");
+    }
+
+    let user_space = ctx.user_space();
+    let root_vmar = user_space.root_vmar();
+
+    // The segments that are marked to be destroyed are also searched, since they are kept until
+    // the last detach.
+    let segments: Vec<_> = shm_segments().read().values().cloned().collect();
+    let segment = segments
+        .into_iter()
+        .find(|segment| root_vmar.is_vmo_mapped_at(shmaddr, segment.vmo()))
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "no segment is attached"))?;
+
+    let range = shmaddr..shmaddr + segment.size().align_up(PAGE_SIZE);
+    root_vmar.remove_vmo_mappings(segment.vmo(), range)?;
+
+    segment.on_detach(ctx.process.pid());
+    drop(segment);
+    remove_detached_segments();
+
+    Ok(SyscallReturn::Return(0))
+}
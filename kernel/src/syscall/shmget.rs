@@ -0,0 +1,46 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::SyscallReturn;
+use crate::{
+    ipc::{
+        ids::IpcObject,
+        key_t,
+        shm::{remove_detached_segments, shm_segments, ShmSegment, SHMMAX, SHMMIN},
+        IpcFlags, PermissionMode,
+    },
+    prelude::*,
+};
+
+pub fn sys_shmget(key: key_t, size: usize, shmflg: i32, ctx: &Context) -> Result<SyscallReturn> {
+    let flags = IpcFlags::from_bits_truncate(shmflg as u32);
+    let mode = (shmflg as u32 & 0o777) as u16;
+    let credentials = ctx.posix_thread.credentials();
+
+    debug!(
+        "[sys_shmget] key = {}, size = {}, flags = {:?}",
+        key, size, shmflg
+    );
+
+    remove_detached_segments();
+
+    let id = shm_segments().get_or_create(
+        key,
+        flags,
+        |segment| {
+            if size > segment.size() {
+                return_errno_with_message!(Errno::EINVAL, "the segment is too small");
+            }
+            segment
+                .permission()
+                .check_access(&credentials, PermissionMode::from_requested_mode(mode))
+        },
+        |_| {
+            if !(SHMMIN..=SHMMAX).contains(&size) {
+                return_errno_with_message!(Errno::EINVAL, "invalid segment size");
+            }
+            ShmSegment::new(key, size, mode, &credentials, ctx.process.pid())
+        },
+    )?;
+
+    Ok(SyscallReturn::Return(id as _))
+}
@@ -95,7 +95,7 @@ fn create_new_signalfd(
     register_observer(ctx, &signal_file, mask)?;
 
     let file_table = ctx.thread_local.file_table().borrow();
-    let fd = file_table.write().insert(signal_file, fd_flags);
+    let fd = file_table.write().insert(signal_file, fd_flags)?;
     Ok(fd)
 }
 
@@ -298,6 +298,7 @@ impl FileLike for SignalFile {
             atime: now,
             mtime: now,
             ctime: now,
+            btime: None,
             type_: InodeType::NamedPipe,
             mode: InodeMode::from_bits_truncate(0o400),
             nlinks: 1,
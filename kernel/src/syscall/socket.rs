@@ -4,11 +4,17 @@ use super::SyscallReturn;
 use crate::{
     fs::{file_handle::FileLike, file_table::FdFlags},
     net::socket::{
-        ip::{datagram::DatagramSocket, stream::StreamSocket},
-        unix::UnixStreamSocket,
+        ip::{datagram::DatagramSocket, raw::RawSocket, stream::StreamSocket, IpFamily},
+        netlink::{
+            NetlinkGenericSocket, NetlinkNetfilterSocket, NetlinkRouteSocket,
+            StandardNetlinkProtocol,
+        },
+        packet::PacketSocket,
+        unix::{UnixDatagramSocket, UnixStreamSocket},
         vsock::VsockStreamSocket,
     },
     prelude::*,
+    security::hooks,
     util::net::{CSocketAddrFamily, Protocol, SockFlags, SockType, SOCK_TYPE_MASK},
 };
 
@@ -16,32 +22,120 @@ pub fn sys_socket(domain: i32, type_: i32, protocol: i32, ctx: &Context) -> Resu
     let domain = CSocketAddrFamily::try_from(domain)?;
     let sock_type = SockType::try_from(type_ & SOCK_TYPE_MASK)?;
     let sock_flags = SockFlags::from_bits_truncate(type_ & !SOCK_TYPE_MASK);
+    let nonblocking = sock_flags.contains(SockFlags::SOCK_NONBLOCK);
+    hooks::socket_create(domain, sock_type, protocol)?;
+
+    // Netlink sockets have their own protocol numbers.
+    if domain == CSocketAddrFamily::AF_NETLINK {
+        let protocol = StandardNetlinkProtocol::try_from(protocol).map_err(|_| {
+            Error::with_message(Errno::EPROTONOSUPPORT, "the netlink protocol is unknown")
+        })?;
+        debug!(
+            "domain = {:?}, sock_type = {:?}, sock_flags = {:?}, protocol = {:?}",
+            domain, sock_type, sock_flags, protocol
+        );
+        let file_like = match (sock_type, protocol) {
+            (SockType::SOCK_RAW | SockType::SOCK_DGRAM, StandardNetlinkProtocol::ROUTE) => {
+                NetlinkRouteSocket::new(nonblocking) as Arc<dyn FileLike>
+            }
+            (SockType::SOCK_RAW | SockType::SOCK_DGRAM, StandardNetlinkProtocol::NETFILTER) => {
+                NetlinkNetfilterSocket::new(nonblocking) as Arc<dyn FileLike>
+            }
+            (SockType::SOCK_RAW | SockType::SOCK_DGRAM, StandardNetlinkProtocol::GENERIC) => {
+                NetlinkGenericSocket::new(nonblocking) as Arc<dyn FileLike>
+            }
+            (SockType::SOCK_RAW | SockType::SOCK_DGRAM, _) => return_errno_with_message!(
+                Errno::EPROTONOSUPPORT,
+                "the netlink protocol is not supported"
+            ),
+            _ => return_errno_with_message!(
+                Errno::ESOCKTNOSUPPORT,
+                "the socket type is not supported for netlink sockets"
+            ),
+        };
+        return install_socket(file_like, sock_flags, ctx);
+    }
+
+    // Packet sockets use EtherTypes in network byte order as their protocol numbers.
+    if domain == CSocketAddrFamily::AF_PACKET {
+        let protocol = u16::from_be(protocol as u16);
+        debug!(
+            "domain = {:?}, sock_type = {:?}, sock_flags = {:?}, protocol = {:#06x}",
+            domain, sock_type, sock_flags, protocol
+        );
+        let file_like = match sock_type {
+            SockType::SOCK_RAW => {
+                PacketSocket::new_raw(nonblocking, protocol)? as Arc<dyn FileLike>
+            }
+            SockType::SOCK_DGRAM => {
+                PacketSocket::new_dgram(nonblocking, protocol)? as Arc<dyn FileLike>
+            }
+            _ => return_errno_with_message!(
+                Errno::ESOCKTNOSUPPORT,
+                "the socket type is not supported for packet sockets"
+            ),
+        };
+        return install_socket(file_like, sock_flags, ctx);
+    }
+
     let protocol = Protocol::try_from(protocol)?;
     debug!(
         "domain = {:?}, sock_type = {:?}, sock_flags = {:?}, protocol = {:?}",
         domain, sock_type, sock_flags, protocol
     );
-    let nonblocking = sock_flags.contains(SockFlags::SOCK_NONBLOCK);
     let file_like = match (domain, sock_type, protocol) {
         // FIXME: SOCK_SEQPACKET is added to run fcntl_test, not supported yet.
         (CSocketAddrFamily::AF_UNIX, SockType::SOCK_STREAM | SockType::SOCK_SEQPACKET, _) => {
             UnixStreamSocket::new(nonblocking) as Arc<dyn FileLike>
         }
+        (CSocketAddrFamily::AF_UNIX, SockType::SOCK_DGRAM, _) => {
+            UnixDatagramSocket::new(nonblocking) as Arc<dyn FileLike>
+        }
         (
             CSocketAddrFamily::AF_INET,
             SockType::SOCK_STREAM,
             Protocol::IPPROTO_IP | Protocol::IPPROTO_TCP,
-        ) => StreamSocket::new(nonblocking) as Arc<dyn FileLike>,
+        ) => StreamSocket::new(nonblocking, IpFamily::V4) as Arc<dyn FileLike>,
+        (
+            CSocketAddrFamily::AF_INET6,
+            SockType::SOCK_STREAM,
+            Protocol::IPPROTO_IP | Protocol::IPPROTO_TCP,
+        ) => StreamSocket::new(nonblocking, IpFamily::V6) as Arc<dyn FileLike>,
         (
             CSocketAddrFamily::AF_INET,
             SockType::SOCK_DGRAM,
             Protocol::IPPROTO_IP | Protocol::IPPROTO_UDP,
-        ) => DatagramSocket::new(nonblocking) as Arc<dyn FileLike>,
+        ) => DatagramSocket::new(nonblocking, IpFamily::V4) as Arc<dyn FileLike>,
+        (
+            CSocketAddrFamily::AF_INET6,
+            SockType::SOCK_DGRAM,
+            Protocol::IPPROTO_IP | Protocol::IPPROTO_UDP,
+        ) => DatagramSocket::new(nonblocking, IpFamily::V6) as Arc<dyn FileLike>,
+        (CSocketAddrFamily::AF_INET, SockType::SOCK_DGRAM, Protocol::IPPROTO_ICMP) => {
+            RawSocket::new_ping(nonblocking, IpFamily::V4)? as Arc<dyn FileLike>
+        }
+        (CSocketAddrFamily::AF_INET6, SockType::SOCK_DGRAM, Protocol::IPPROTO_ICMPV6) => {
+            RawSocket::new_ping(nonblocking, IpFamily::V6)? as Arc<dyn FileLike>
+        }
+        (CSocketAddrFamily::AF_INET, SockType::SOCK_RAW, _) => {
+            RawSocket::new_raw(nonblocking, IpFamily::V4, protocol)? as Arc<dyn FileLike>
+        }
+        (CSocketAddrFamily::AF_INET6, SockType::SOCK_RAW, _) => {
+            RawSocket::new_raw(nonblocking, IpFamily::V6, protocol)? as Arc<dyn FileLike>
+        }
         (CSocketAddrFamily::AF_VSOCK, SockType::SOCK_STREAM, _) => {
             Arc::new(VsockStreamSocket::new(nonblocking)) as Arc<dyn FileLike>
         }
         _ => return_errno_with_message!(Errno::EAFNOSUPPORT, "unsupported domain"),
     };
+    install_socket(file_like, sock_flags, ctx)
+}
+
+fn install_socket(
+    file_like: Arc<dyn FileLike>,
+    sock_flags: SockFlags,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
     let fd = {
         let file_table = ctx.thread_local.file_table().borrow();
         let mut file_table_locked = file_table.write();
@@ -50,7 +144,7 @@ pub fn sys_socket(domain: i32, type_: i32, protocol: i32, ctx: &Context) -> Resu
         } else {
             FdFlags::empty()
         };
-        file_table_locked.insert(file_like, fd_flags)
+        file_table_locked.insert(file_like, fd_flags)?
     };
     Ok(SyscallReturn::Return(fd as _))
 }
@@ -0,0 +1,368 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! `splice()` moves data between a pipe and another file without copying it through
+//! user memory.
+//!
+//! The data are copied between the pipe buffer and the page cache (or the buffer of
+//! another pipe) directly. The helpers here are shared with `sendfile()` and
+//! `copy_file_range()`, which write the cached pages of the input file to the output
+//! file directly.
+
+use align_ext::AlignExt;
+use aster_rights::Full;
+use ostd::mm::UntypedMem;
+
+use super::SyscallReturn;
+use crate::{
+    fs::{
+        file_handle::FileLike,
+        file_table::{FileDesc, WithFileTable},
+        inode_handle::InodeHandle,
+        pipe::{PipeReader, PipeWriter},
+        utils::{Inode, InodeType, SeekFrom, StatusFlags},
+    },
+    prelude::*,
+    vm::vmo::Vmo,
+};
+
+/// The maximum number of bytes that can be moved by a single call.
+pub(super) const MAX_COUNT: usize = 0x7fff_f000;
+
+pub fn sys_splice(
+    fd_in: FileDesc,
+    off_in_ptr: Vaddr,
+    fd_out: FileDesc,
+    off_out_ptr: Vaddr,
+    len: usize,
+    flags: u32,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let flags = SpliceFlags::from_bits(flags)
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "unknown flags"))?;
+    let mut off_in = read_offset_from_user(off_in_ptr, ctx)?;
+    let mut off_out = read_offset_from_user(off_out_ptr, ctx)?;
+    debug!(
+        "fd_in = {}, off_in = {:?}, fd_out = {}, off_out = {:?}, len = 0x{:x}, flags = {:?}",
+        fd_in, off_in, fd_out, off_out, len, flags
+    );
+
+    let (in_file, out_file) = get_file_pair(fd_in, fd_out, ctx)?;
+    let len = len.min(MAX_COUNT);
+    let is_nonblocking = flags.contains(SpliceFlags::SPLICE_F_NONBLOCK);
+
+    let in_pipe = in_file.downcast_ref::<PipeReader>();
+    let out_pipe = out_file.downcast_ref::<PipeWriter>();
+    if (in_pipe.is_some() && off_in.is_some()) || (out_pipe.is_some() && off_out.is_some()) {
+        return_errno_with_message!(Errno::ESPIPE, "a pipe does not have offsets");
+    }
+
+    let spliced_len = match (in_pipe, out_pipe) {
+        (Some(in_pipe), Some(out_pipe)) => {
+            splice_pipe_to_pipe(in_pipe, out_pipe, len, is_nonblocking)?
+        }
+        (Some(in_pipe), None) => {
+            check_writable(&out_file)?;
+            if out_file.status_flags().contains(StatusFlags::O_APPEND) {
+                return_errno_with_message!(Errno::EINVAL, "the output file is append-only");
+            }
+            let is_nonblocking =
+                is_nonblocking || in_pipe.status_flags().contains(StatusFlags::O_NONBLOCK);
+            in_pipe.splice_to(len, is_nonblocking, |reader| {
+                write_to_file(&out_file, off_out.as_mut(), reader)
+            })?
+        }
+        (None, Some(out_pipe)) => {
+            check_readable(&in_file)?;
+            let is_nonblocking =
+                is_nonblocking || out_pipe.status_flags().contains(StatusFlags::O_NONBLOCK);
+            out_pipe.splice_from(len, is_nonblocking, |writer| {
+                read_from_file(&in_file, off_in.as_mut(), writer)
+            })?
+        }
+        (None, None) => {
+            return_errno_with_message!(Errno::EINVAL, "neither of the files is a pipe");
+        }
+    };
+
+    write_offset_to_user(off_in_ptr, off_in, ctx)?;
+    write_offset_to_user(off_out_ptr, off_out, ctx)?;
+
+    Ok(SyscallReturn::Return(spliced_len as _))
+}
+
+/// Moves the data from one pipe to another, which is copied between the two pipe
+/// buffers directly.
+fn splice_pipe_to_pipe(
+    in_pipe: &PipeReader,
+    out_pipe: &PipeWriter,
+    len: usize,
+    is_nonblocking: bool,
+) -> Result<usize> {
+    if in_pipe.is_peer_of(out_pipe) {
+        return_errno_with_message!(Errno::EINVAL, "the input and output are the same pipe");
+    }
+
+    // Both ends either block or not, so a blocking wait for the input pipe is never
+    // failed by a full output pipe.
+    let is_nonblocking = is_nonblocking
+        || in_pipe.status_flags().contains(StatusFlags::O_NONBLOCK)
+        || out_pipe.status_flags().contains(StatusFlags::O_NONBLOCK);
+
+    let mut moved_len = 0;
+    in_pipe.splice_to(len, is_nonblocking, |reader| {
+        // Do not wait for the space of the output pipe if some data have been moved.
+        let result =
+            out_pipe.splice_from(reader.remain(), is_nonblocking || moved_len > 0, |writer| {
+                Ok(writer.write_fallible(reader)?)
+            });
+        match result {
+            Ok(len) => {
+                moved_len += len;
+                Ok(len)
+            }
+            Err(err) if err.error() == Errno::EAGAIN && moved_len > 0 => Ok(0),
+            Err(err) => Err(err),
+        }
+    })
+}
+
+/// Copies at most `count` bytes from `in_file` to `out_file`.
+///
+/// If `in_file` is a regular file, the data are written to `out_file` straight from
+/// its page cache. Otherwise, the data are copied through a kernel buffer.
+///
+/// The offsets are advanced by the number of bytes copied, where `None` stands for
+/// the file offset.
+pub(super) fn copy_between_files(
+    in_file: &Arc<dyn FileLike>,
+    in_offset: Option<&mut usize>,
+    out_file: &Arc<dyn FileLike>,
+    out_offset: Option<&mut usize>,
+    count: usize,
+) -> Result<usize> {
+    let Some((in_handle, pages)) = page_cache_of(in_file) else {
+        return copy_through_buffer(in_file, in_offset, out_file, out_offset, count);
+    };
+
+    let Some(in_offset) = in_offset else {
+        // The file offset is updated after the data are copied.
+        let mut offset = in_handle.offset();
+        let copied_len = copy_from_page_cache(
+            in_handle.dentry().inode(),
+            &pages,
+            &mut offset,
+            out_file,
+            out_offset,
+            count,
+        )?;
+        in_file.seek(SeekFrom::Start(offset))?;
+        return Ok(copied_len);
+    };
+
+    copy_from_page_cache(
+        in_handle.dentry().inode(),
+        &pages,
+        in_offset,
+        out_file,
+        out_offset,
+        count,
+    )
+}
+
+/// Returns the page cache of a regular file which is not opened with `O_DIRECT`.
+fn page_cache_of(file: &Arc<dyn FileLike>) -> Option<(&InodeHandle, Vmo<Full>)> {
+    let inode_handle = file.downcast_ref::<InodeHandle>()?;
+    if file.status_flags().contains(StatusFlags::O_DIRECT) {
+        return None;
+    }
+
+    let inode = inode_handle.dentry().inode();
+    if inode.type_() != InodeType::File {
+        return None;
+    }
+    let pages = inode.page_cache()?;
+    Some((inode_handle, pages))
+}
+
+fn copy_from_page_cache(
+    inode: &Arc<dyn Inode>,
+    pages: &Vmo<Full>,
+    offset: &mut usize,
+    out_file: &Arc<dyn FileLike>,
+    mut out_offset: Option<&mut usize>,
+    count: usize,
+) -> Result<usize> {
+    let mut copied_len = 0;
+
+    while copied_len < count {
+        let file_size = inode.size();
+        if *offset >= file_size {
+            break;
+        }
+
+        let page_offset = (*offset).align_down(PAGE_SIZE);
+        let offset_in_page = *offset - page_offset;
+        let len = (PAGE_SIZE - offset_in_page)
+            .min(file_size - *offset)
+            .min(count - copied_len);
+
+        let write_res = pages.commit_page(page_offset).and_then(|frame| {
+            let mut reader = frame.reader().to_fallible();
+            reader.skip(offset_in_page).limit(len);
+            write_to_file(out_file, out_offset.as_deref_mut(), &mut reader)
+        });
+        let written_len = match write_res {
+            Ok(len) => len,
+            Err(err) if copied_len > 0 => {
+                warn!("error occurs when trying to copy file: {:?}", err);
+                break;
+            }
+            Err(err) => return Err(err),
+        };
+
+        *offset += written_len;
+        copied_len += written_len;
+        if written_len < len {
+            break;
+        }
+    }
+
+    Ok(copied_len)
+}
+
+fn copy_through_buffer(
+    in_file: &Arc<dyn FileLike>,
+    mut in_offset: Option<&mut usize>,
+    out_file: &Arc<dyn FileLike>,
+    mut out_offset: Option<&mut usize>,
+    count: usize,
+) -> Result<usize> {
+    const BUFFER_SIZE: usize = PAGE_SIZE;
+
+    let mut buffer = vec![0u8; BUFFER_SIZE].into_boxed_slice();
+    let mut copied_len = 0;
+
+    while copied_len < count {
+        let max_read_len = BUFFER_SIZE.min(count - copied_len);
+        let mut writer = VmWriter::from(&mut buffer[..max_read_len]).to_fallible();
+        let read_len = match read_from_file(in_file, in_offset.as_deref_mut(), &mut writer) {
+            Ok(len) => len,
+            Err(err) if copied_len > 0 => {
+                warn!("error occurs when trying to read file: {:?}", err);
+                break;
+            }
+            Err(err) => return Err(err),
+        };
+        if read_len == 0 {
+            break;
+        }
+
+        // Short reads and short writes are all acceptable.
+        let mut reader = VmReader::from(&buffer[..read_len]).to_fallible();
+        let written_len = match write_to_file(out_file, out_offset.as_deref_mut(), &mut reader) {
+            Ok(len) => len,
+            Err(err) if copied_len > 0 => {
+                warn!("error occurs when trying to write file: {:?}", err);
+                break;
+            }
+            Err(err) => return Err(err),
+        };
+
+        copied_len += written_len;
+        if written_len < max_read_len {
+            break;
+        }
+    }
+
+    Ok(copied_len)
+}
+
+/// Reads from the file at the offset, or at the file offset if the offset is `None`.
+pub(super) fn read_from_file(
+    file: &Arc<dyn FileLike>,
+    offset: Option<&mut usize>,
+    writer: &mut VmWriter,
+) -> Result<usize> {
+    let Some(offset) = offset else {
+        return file.read(writer);
+    };
+    let read_len = file.read_at(*offset, writer)?;
+    *offset += read_len;
+    Ok(read_len)
+}
+
+/// Writes to the file at the offset, or at the file offset if the offset is `None`.
+fn write_to_file(
+    file: &Arc<dyn FileLike>,
+    offset: Option<&mut usize>,
+    reader: &mut VmReader,
+) -> Result<usize> {
+    let Some(offset) = offset else {
+        return file.write(reader);
+    };
+    let written_len = file.write_at(*offset, reader)?;
+    *offset += written_len;
+    Ok(written_len)
+}
+
+pub(super) fn get_file_pair(
+    in_fd: FileDesc,
+    out_fd: FileDesc,
+    ctx: &Context,
+) -> Result<(Arc<dyn FileLike>, Arc<dyn FileLike>)> {
+    ctx.thread_local
+        .file_table()
+        .borrow_mut()
+        .read_with(|inner| {
+            let in_file = inner.get_file(in_fd)?.clone();
+            let out_file = inner.get_file(out_fd)?.clone();
+            Ok((in_file, out_file))
+        })
+}
+
+pub(super) fn check_readable(file: &Arc<dyn FileLike>) -> Result<()> {
+    if !file.access_mode().is_readable() {
+        return_errno_with_message!(Errno::EBADF, "the file is not opened for reading");
+    }
+    Ok(())
+}
+
+pub(super) fn check_writable(file: &Arc<dyn FileLike>) -> Result<()> {
+    if !file.access_mode().is_writable() {
+        return_errno_with_message!(Errno::EBADF, "the file is not opened for writing");
+    }
+    Ok(())
+}
+
+/// Reads the optional `loff_t` offset from the user space.
+pub(super) fn read_offset_from_user(offset_ptr: Vaddr, ctx: &Context) -> Result<Option<usize>> {
+    if offset_ptr == 0 {
+        return Ok(None);
+    }
+
+    let offset: i64 = ctx.user_space().read_val(offset_ptr)?;
+    if offset < 0 {
+        return_errno_with_message!(Errno::EINVAL, "offset cannot be negative");
+    }
+    Ok(Some(offset as usize))
+}
+
+pub(super) fn write_offset_to_user(
+    offset_ptr: Vaddr,
+    offset: Option<usize>,
+    ctx: &Context,
+) -> Result<()> {
+    if let Some(offset) = offset {
+        ctx.user_space().write_val(offset_ptr, &(offset as i64))?;
+    }
+    Ok(())
+}
+
+bitflags! {
+    pub(super) struct SpliceFlags: u32 {
+        const SPLICE_F_MOVE     = 1 << 0;
+        const SPLICE_F_NONBLOCK = 1 << 1;
+        const SPLICE_F_MORE     = 1 << 2;
+        const SPLICE_F_GIFT     = 1 << 3;
+    }
+}
@@ -1,8 +1,11 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use core::time::Duration;
+
 use super::SyscallReturn;
 use crate::{
     fs::{
+        device::DeviceId,
         file_table::{get_file_fast, FileDesc},
         fs_resolver::{FsPath, AT_FDCWD},
         utils::Metadata,
@@ -45,6 +48,51 @@ pub fn sys_fstatat(
     flags: u32,
     ctx: &Context,
 ) -> Result<SyscallReturn> {
+    let metadata = fstatat_metadata(dirfd, filename_ptr, stat_buf_ptr, flags, ctx)?;
+    let stat = Stat::from(metadata);
+    ctx.user_space().write_val(stat_buf_ptr, &stat)?;
+    Ok(SyscallReturn::Return(0))
+}
+
+/// The `fstat64` system call of 32-bit programs.
+pub fn compat_sys_fstat64(
+    fd: FileDesc,
+    stat_buf_ptr: Vaddr,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    debug!("fd = {}, stat_buf_addr = 0x{:x}", fd, stat_buf_ptr);
+
+    let mut file_table = ctx.thread_local.file_table().borrow_mut();
+    let file = get_file_fast!(&mut file_table, fd);
+
+    let stat = CompatStat64::from(file.metadata());
+    ctx.user_space().write_val(stat_buf_ptr, &stat)?;
+
+    Ok(SyscallReturn::Return(0))
+}
+
+/// The `fstatat64` system call of 32-bit programs.
+pub fn compat_sys_fstatat64(
+    dirfd: FileDesc,
+    filename_ptr: Vaddr,
+    stat_buf_ptr: Vaddr,
+    flags: u32,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let metadata = fstatat_metadata(dirfd, filename_ptr, stat_buf_ptr, flags, ctx)?;
+    let stat = CompatStat64::from(metadata);
+    ctx.user_space().write_val(stat_buf_ptr, &stat)?;
+    Ok(SyscallReturn::Return(0))
+}
+
+/// Looks up the metadata of the file for `fstatat` and its variants.
+fn fstatat_metadata(
+    dirfd: FileDesc,
+    filename_ptr: Vaddr,
+    stat_buf_ptr: Vaddr,
+    flags: u32,
+    ctx: &Context,
+) -> Result<Metadata> {
     let user_space = ctx.user_space();
     let filename = user_space.read_cstring(filename_ptr, MAX_FILENAME_LEN)?;
     let flags =
@@ -59,7 +107,9 @@ pub fn sys_fstatat(
             return_errno_with_message!(Errno::ENOENT, "path is empty");
         }
         // In this case, the behavior of fstatat() is similar to that of fstat().
-        return self::sys_fstat(dirfd, stat_buf_ptr, ctx);
+        let mut file_table = ctx.thread_local.file_table().borrow_mut();
+        let file = get_file_fast!(&mut file_table, dirfd);
+        return Ok(file.metadata());
     }
 
     let dentry = {
@@ -72,8 +122,56 @@ pub fn sys_fstatat(
             fs.lookup(&fs_path)?
         }
     };
-    let stat = Stat::from(dentry.metadata());
-    user_space.write_val(stat_buf_ptr, &stat)?;
+    Ok(dentry.metadata())
+}
+
+pub fn sys_statx(
+    dirfd: FileDesc,
+    filename_ptr: Vaddr,
+    flags: u32,
+    mask: u32,
+    statx_buf_ptr: Vaddr,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let user_space = ctx.user_space();
+    let filename = user_space.read_cstring(filename_ptr, MAX_FILENAME_LEN)?;
+    let flags =
+        StatxFlags::from_bits(flags).ok_or(Error::with_message(Errno::EINVAL, "invalid flags"))?;
+    let mask = StatxMask::from_bits_truncate(mask);
+    debug!(
+        "dirfd = {}, filename = {:?}, flags = {:?}, mask = {:?}, statx_buf_ptr = 0x{:x}",
+        dirfd, filename, flags, mask, statx_buf_ptr
+    );
+
+    if flags.contains(StatxFlags::AT_STATX_SYNC_TYPE) {
+        return_errno_with_message!(Errno::EINVAL, "invalid sync type");
+    }
+    if mask.contains(StatxMask::STATX__RESERVED) {
+        return_errno_with_message!(Errno::EINVAL, "the reserved mask bit is set");
+    }
+
+    let metadata = if filename.is_empty() {
+        if !flags.contains(StatxFlags::AT_EMPTY_PATH) {
+            return_errno_with_message!(Errno::ENOENT, "path is empty");
+        }
+        let mut file_table = ctx.thread_local.file_table().borrow_mut();
+        let file = get_file_fast!(&mut file_table, dirfd);
+        file.metadata()
+    } else {
+        let filename = filename.to_string_lossy();
+        let fs_path = FsPath::new(dirfd, filename.as_ref())?;
+        let fs = ctx.posix_thread.fs().resolver().read();
+        let dentry = if flags.contains(StatxFlags::AT_SYMLINK_NOFOLLOW) {
+            fs.lookup_no_follow(&fs_path)?
+        } else {
+            fs.lookup(&fs_path)?
+        };
+        dentry.metadata()
+    };
+
+    // The cached metadata is always up-to-date, so the sync types are ignored.
+    let statx = Statx::new(&metadata, mask);
+    user_space.write_val(statx_buf_ptr, &statx)?;
     Ok(SyscallReturn::Return(0))
 }
 
@@ -135,6 +233,75 @@ impl From<Metadata> for Stat {
     }
 }
 
+/// File Stat of 32-bit programs, i.e., `struct stat64` of the generic architectures
+#[derive(Debug, Clone, Copy, Pod, Default)]
+#[repr(C)]
+pub struct CompatStat64 {
+    /// ID of device containing file
+    st_dev: u64,
+    /// Inode number
+    st_ino: u64,
+    /// File type and mode
+    st_mode: u32,
+    /// Number of hard links
+    st_nlink: u32,
+    /// User ID of owner
+    st_uid: u32,
+    /// Group ID of owner
+    st_gid: u32,
+    /// Device ID (if special file)
+    st_rdev: u64,
+    /// Padding bytes
+    __pad1: u64,
+    /// Total size, in bytes
+    st_size: i64,
+    /// Block size for filesystem I/O
+    st_blksize: i32,
+    /// Padding bytes
+    __pad2: i32,
+    /// Number of 512-byte blocks allocated
+    st_blocks: i64,
+    /// Time of last access
+    st_atime: i32,
+    st_atime_nsec: u32,
+    /// Time of last modification
+    st_mtime: i32,
+    st_mtime_nsec: u32,
+    /// Time of last status change
+    st_ctime: i32,
+    st_ctime_nsec: u32,
+    /// Unused field
+    __unused: [u32; 2],
+}
+
+impl From<Metadata> for CompatStat64 {
+    fn from(info: Metadata) -> Self {
+        // The timestamps after 2038 cannot be represented, which is the limitation of
+        // `struct stat64`. The programs should use `statx` instead.
+        Self {
+            st_dev: info.dev,
+            st_ino: info.ino,
+            st_mode: info.type_ as u32 | info.mode.bits() as u32,
+            st_nlink: info.nlinks as u32,
+            st_uid: info.uid.into(),
+            st_gid: info.gid.into(),
+            st_rdev: info.rdev,
+            __pad1: 0,
+            st_size: info.size as i64,
+            st_blksize: info.blk_size as i32,
+            __pad2: 0,
+            st_blocks: (info.blocks * (info.blk_size / 512)) as i64, // Number of 512B blocks
+            st_atime: info.atime.as_secs() as i32,
+            st_atime_nsec: info.atime.subsec_nanos(),
+            st_mtime: info.mtime.as_secs() as i32,
+            st_mtime_nsec: info.mtime.subsec_nanos(),
+            st_ctime: info.ctime.as_secs() as i32,
+            st_ctime_nsec: info.ctime.subsec_nanos(),
+            __unused: [0; 2],
+        }
+    }
+}
+
 bitflags::bitflags! {
     struct StatFlags: u32 {
         const AT_EMPTY_PATH = 1 << 12;
@@ -142,3 +309,155 @@ bitflags::bitflags! {
         const AT_SYMLINK_NOFOLLOW = 1 << 8;
     }
 }
+
+bitflags::bitflags! {
+    struct StatxFlags: u32 {
+        const AT_SYMLINK_NOFOLLOW = 1 << 8;
+        const AT_NO_AUTOMOUNT = 1 << 11;
+        const AT_EMPTY_PATH = 1 << 12;
+        const AT_STATX_FORCE_SYNC = 1 << 13;
+        const AT_STATX_DONT_SYNC = 1 << 14;
+        /// The invalid combination of the sync types.
+        const AT_STATX_SYNC_TYPE = Self::AT_STATX_FORCE_SYNC.bits | Self::AT_STATX_DONT_SYNC.bits;
+    }
+}
+
+bitflags::bitflags! {
+    /// The fields of [`Statx`] that are requested by the user or filled by the kernel.
+    struct StatxMask: u32 {
+        const STATX_TYPE = 1 << 0;
+        const STATX_MODE = 1 << 1;
+        const STATX_NLINK = 1 << 2;
+        const STATX_UID = 1 << 3;
+        const STATX_GID = 1 << 4;
+        const STATX_ATIME = 1 << 5;
+        const STATX_MTIME = 1 << 6;
+        const STATX_CTIME = 1 << 7;
+        const STATX_INO = 1 << 8;
+        const STATX_SIZE = 1 << 9;
+        const STATX_BLOCKS = 1 << 10;
+        const STATX_BASIC_STATS = 0x7ff;
+        const STATX_BTIME = 1 << 11;
+        const STATX__RESERVED = 1 << 31;
+    }
+}
+
+/// File Stat of `statx`
+#[derive(Debug, Clone, Copy, Pod, Default)]
+#[repr(C)]
+pub struct Statx {
+    /// Mask of the filled fields
+    stx_mask: u32,
+    /// Block size for filesystem I/O
+    stx_blksize: u32,
+    /// Extra file attributes
+    stx_attributes: u64,
+    /// Number of hard links
+    stx_nlink: u32,
+    /// User ID of owner
+    stx_uid: u32,
+    /// Group ID of owner
+    stx_gid: u32,
+    /// File type and mode
+    stx_mode: u16,
+    __spare0: u16,
+    /// Inode number
+    stx_ino: u64,
+    /// Total size, in bytes
+    stx_size: u64,
+    /// Number of 512-byte blocks allocated
+    stx_blocks: u64,
+    /// Mask of the supported attributes
+    stx_attributes_mask: u64,
+    /// Time of last access
+    stx_atime: StatxTimestamp,
+    /// Time of creation
+    stx_btime: StatxTimestamp,
+    /// Time of last status change
+    stx_ctime: StatxTimestamp,
+    /// Time of last modification
+    stx_mtime: StatxTimestamp,
+    /// Device ID (if special file)
+    stx_rdev_major: u32,
+    stx_rdev_minor: u32,
+    /// ID of device containing file
+    stx_dev_major: u32,
+    stx_dev_minor: u32,
+    /// Unused fields
+    __spare2: [u64; 14],
+}
+
+impl Statx {
+    /// Fills the requested fields which are available in the metadata.
+    fn new(info: &Metadata, mask: StatxMask) -> Self {
+        let mut filled = mask & StatxMask::STATX_BASIC_STATS;
+        if info.btime.is_some() {
+            filled |= mask & StatxMask::STATX_BTIME;
+        }
+
+        let mut statx = Self {
+            stx_mask: filled.bits(),
+            stx_blksize: info.blk_size as u32,
+            stx_rdev_major: DeviceId::from(info.rdev).major(),
+            stx_rdev_minor: DeviceId::from(info.rdev).minor(),
+            stx_dev_major: DeviceId::from(info.dev).major(),
+            stx_dev_minor: DeviceId::from(info.dev).minor(),
+            ..Default::default()
+        };
+        if filled.contains(StatxMask::STATX_TYPE) {
+            statx.stx_mode |= info.type_ as u16;
+        }
+        if filled.contains(StatxMask::STATX_MODE) {
+            statx.stx_mode |= info.mode.bits();
+        }
+        if filled.contains(StatxMask::STATX_NLINK) {
+            statx.stx_nlink = info.nlinks as u32;
+        }
+        if filled.contains(StatxMask::STATX_UID) {
+            statx.stx_uid = info.uid.into();
+        }
+        if filled.contains(StatxMask::STATX_GID) {
+            statx.stx_gid = info.gid.into();
+        }
+        if filled.contains(StatxMask::STATX_ATIME) {
+            statx.stx_atime = info.atime.into();
+        }
+        if filled.contains(StatxMask::STATX_MTIME) {
+            statx.stx_mtime = info.mtime.into();
+        }
+        if filled.contains(StatxMask::STATX_CTIME) {
+            statx.stx_ctime = info.ctime.into();
+        }
+        if filled.contains(StatxMask::STATX_INO) {
+            statx.stx_ino = info.ino;
+        }
+        if filled.contains(StatxMask::STATX_SIZE) {
+            statx.stx_size = info.size as u64;
+        }
+        if filled.contains(StatxMask::STATX_BLOCKS) {
+            statx.stx_blocks = (info.blocks * (info.blk_size / 512)) as u64;
+        }
+        if filled.contains(StatxMask::STATX_BTIME) {
+            statx.stx_btime = info.btime.unwrap().into();
+        }
+        statx
+    }
+}
+
+#[derive(Debug, Clone, Copy, Pod, Default)]
+#[repr(C)]
+struct StatxTimestamp {
+    tv_sec: i64,
+    tv_nsec: u32,
+    __reserved: i32,
+}
+
+impl From<Duration> for StatxTimestamp {
+    fn from(duration: Duration) -> Self {
+        Self {
+            tv_sec: duration.as_secs() as i64,
+            tv_nsec: duration.subsec_nanos(),
+            __reserved: 0,
+        }
+    }
+}
@@ -0,0 +1,107 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The in-kernel syscall tracer.
+//!
+//! The syscalls of a traced process are logged to the console with their numbers, names,
+//! arguments, and return values, e.g.,
+//!
+//! ```text
+//! [strace][pid=42][tid=42] 56 openat(0xffffffffffffff9c, 0x3fffffef10, 0x80000, 0x0) = -2 ENOENT
+//! ```
+//!
+//! A process is traced after `1` is written to `/proc/[pid]/strace`, and so are the child
+//! processes created afterwards. Unlike `strace(1)`, which stops the tracee at the entry and
+//! the exit of each syscall with `ptrace`, the tracer logs in the syscall path of the tracee,
+//! so that it is much faster during bring-up. A syscall is logged when it returns, and its
+//! arguments are shown as the raw values of the registers without being decoded.
+
+use core::fmt;
+
+use super::{arch, SyscallReturn};
+use crate::{context::Context, prelude::*};
+
+/// Logs the syscall if the current process is traced.
+pub(super) fn trace_syscall(
+    ctx: &Context,
+    syscall_number: u64,
+    args: &[u64; 6],
+    is_compat: bool,
+    syscall_return: Result<SyscallReturn>,
+) {
+    if !ctx.process.is_syscall_traced() {
+        return;
+    }
+
+    let info = match is_compat {
+        #[cfg(target_arch = "riscv64")]
+        true => arch::compat::syscall_info(syscall_number),
+        _ => arch::syscall_info(syscall_number),
+    };
+    // The unimplemented syscalls are shown with all the arguments.
+    let (name, nr_args) = match info {
+        Some((name, nr_args)) => (Some(name), nr_args),
+        None => (None, args.len()),
+    };
+
+    println!(
+        "[strace][pid={}][tid={}]{} {} {}({}) = {}",
+        ctx.process.pid(),
+        ctx.posix_thread.tid(),
+        if is_compat { "[compat]" } else { "" },
+        syscall_number,
+        SyscallName(name, syscall_number),
+        SyscallArgs(&args[..nr_args]),
+        SyscallResult(syscall_return),
+    );
+}
+
+/// The name of a syscall, which is the lowercase name of its constant without the `SYS_`
+/// prefix, or `syscall_<number>` as in `strace(1)` if the syscall is unimplemented.
+struct SyscallName(Option<&'static str>, u64);
+
+impl fmt::Display for SyscallName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(name) = self.0 else {
+            return write!(f, "syscall_{:#x}", self.1);
+        };
+        let name = name.strip_prefix("SYS_").unwrap_or(name);
+        for c in name.chars() {
+            fmt::Write::write_char(f, c.to_ascii_lowercase())?;
+        }
+        Ok(())
+    }
+}
+
+struct SyscallArgs<'a>(&'a [u64]);
+
+impl fmt::Display for SyscallArgs<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, arg) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{:#x}", arg)?;
+        }
+        Ok(())
+    }
+}
+
+/// The result of a syscall, where the error is shown with its name and message, and `?` is
+/// shown if the syscall does not set the return value (e.g., `rt_sigreturn`).
+struct SyscallResult(Result<SyscallReturn>);
+
+impl fmt::Display for SyscallResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            Ok(SyscallReturn::Return(return_value)) => write!(f, "{}", return_value),
+            Ok(SyscallReturn::NoReturn) => f.write_str("?"),
+            Err(err) => {
+                write!(f, "-{} {:?}", err.error() as i32, err.error())?;
+                if let Some(message) = err.message() {
+                    write!(f, " ({})", message)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
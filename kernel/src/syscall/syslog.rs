@@ -0,0 +1,188 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::format;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use aster_logger::{LogEntry, LogReadError, CONSOLE_LOGLEVEL_DEFAULT, CONSOLE_LOGLEVEL_MIN};
+
+use super::SyscallReturn;
+use crate::{device::wait_for_log_entries, prelude::*, process::credentials::capabilities::CapSet};
+
+pub fn sys_syslog(action: i32, buf: Vaddr, len: i32, ctx: &Context) -> Result<SyscallReturn> {
+    let action = SyslogAction::try_from(action)?;
+    debug!("action = {:?}, buf = {:#x}, len = {}", action, buf, len);
+
+    check_permission(action, ctx)?;
+
+    let res = match action {
+        SyslogAction::Close | SyslogAction::Open => 0,
+        SyslogAction::Read => {
+            let len = check_buffer(buf, len)?;
+            read(buf, len, ctx)?
+        }
+        SyslogAction::ReadAll | SyslogAction::ReadClear => {
+            let len = check_buffer(buf, len)?;
+            let read_len = read_all(buf, len, ctx)?;
+            if action == SyslogAction::ReadClear {
+                aster_logger::clear();
+            }
+            read_len
+        }
+        SyslogAction::Clear => {
+            aster_logger::clear();
+            0
+        }
+        SyslogAction::ConsoleOff => {
+            aster_logger::set_console_enabled(false);
+            0
+        }
+        SyslogAction::ConsoleOn => {
+            aster_logger::set_console_enabled(true);
+            0
+        }
+        SyslogAction::ConsoleLevel => {
+            if len < CONSOLE_LOGLEVEL_MIN as i32 || len > CONSOLE_LOGLEVEL_DEFAULT as i32 {
+                return_errno_with_message!(Errno::EINVAL, "the console log level is invalid");
+            }
+            aster_logger::set_console_loglevel(len as u8);
+            aster_logger::set_console_enabled(true);
+            0
+        }
+        SyslogAction::SizeUnread => {
+            let seq = SYSLOG_SEQ.load(Ordering::Relaxed);
+            entries_from(seq)
+                .map(|entry| format_syslog_record(&entry).len())
+                .sum()
+        }
+        SyslogAction::SizeBuffer => aster_logger::buffer_size(),
+    };
+
+    Ok(SyscallReturn::Return(res as _))
+}
+
+/// The sequence number of the next entry read by `SYSLOG_ACTION_READ`.
+static SYSLOG_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// Reads the unread entries destructively, waiting if there are none.
+fn read(buf: Vaddr, len: usize, ctx: &Context) -> Result<usize> {
+    wait_for_log_entries(|| {
+        let mut seq = SYSLOG_SEQ.load(Ordering::Relaxed);
+        let mut output = String::new();
+        loop {
+            let entry = match aster_logger::read_entry(seq) {
+                Ok(entry) => entry,
+                Err(LogReadError::Lost(first_seq)) => {
+                    seq = first_seq;
+                    continue;
+                }
+                Err(LogReadError::NotYet) => break,
+            };
+            let record = format_syslog_record(&entry);
+            if output.len() + record.len() > len {
+                if output.is_empty() {
+                    return_errno_with_message!(Errno::EINVAL, "the buffer is too small");
+                }
+                break;
+            }
+            output.push_str(&record);
+            seq = entry.seq + 1;
+        }
+
+        if output.is_empty() {
+            return_errno_with_message!(Errno::EAGAIN, "there are no unread log entries");
+        }
+
+        ctx.user_space()
+            .write_bytes(buf, &mut VmReader::from(output.as_bytes()))?;
+        SYSLOG_SEQ.store(seq, Ordering::Relaxed);
+        Ok(output.len())
+    })
+}
+
+/// Reads the latest entries that fit in the buffer since the log buffer was last cleared.
+fn read_all(buf: Vaddr, len: usize, ctx: &Context) -> Result<usize> {
+    let records: Vec<String> = entries_from(aster_logger::clear_seq())
+        .map(|entry| format_syslog_record(&entry))
+        .collect();
+
+    let mut total_len = 0;
+    let num_kept = records
+        .iter()
+        .rev()
+        .take_while(|record| {
+            total_len += record.len();
+            total_len <= len
+        })
+        .count();
+    let output = records[records.len() - num_kept..].concat();
+
+    ctx.user_space()
+        .write_bytes(buf, &mut VmReader::from(output.as_bytes()))?;
+    Ok(output.len())
+}
+
+/// Returns the available entries starting from the sequence number.
+fn entries_from(mut seq: u64) -> impl Iterator<Item = LogEntry> {
+    core::iter::from_fn(move || loop {
+        match aster_logger::read_entry(seq) {
+            Ok(entry) => {
+                seq = entry.seq + 1;
+                return Some(entry);
+            }
+            Err(LogReadError::Lost(first_seq)) => seq = first_seq,
+            Err(LogReadError::NotYet) => return None,
+        }
+    })
+}
+
+fn format_syslog_record(entry: &LogEntry) -> String {
+    format!(
+        "<{}>[{:5}.{:06}] {}\n",
+        entry.prio,
+        entry.timestamp.as_secs(),
+        entry.timestamp.subsec_micros(),
+        entry.text
+    )
+}
+
+fn check_buffer(buf: Vaddr, len: i32) -> Result<usize> {
+    if buf == 0 || len < 0 {
+        return_errno_with_message!(Errno::EINVAL, "the buffer is invalid");
+    }
+    Ok(len as usize)
+}
+
+/// Checks the permission for the action.
+///
+/// Like Linux with `kernel.dmesg_restrict = 0`, reading all entries and getting the buffer
+/// size are allowed for everyone. The other actions require `CAP_SYSLOG` or `CAP_SYS_ADMIN`.
+fn check_permission(action: SyslogAction, ctx: &Context) -> Result<()> {
+    if matches!(
+        action,
+        SyslogAction::Close | SyslogAction::Open | SyslogAction::ReadAll | SyslogAction::SizeBuffer
+    ) {
+        return Ok(());
+    }
+
+    let capset = ctx.posix_thread.credentials().effective_capset();
+    if !capset.contains(CapSet::SYSLOG) && !capset.contains(CapSet::SYS_ADMIN) {
+        return_errno_with_message!(Errno::EPERM, "`CAP_SYSLOG` is required");
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromInt)]
+#[repr(i32)]
+enum SyslogAction {
+    Close = 0,
+    Open = 1,
+    Read = 2,
+    ReadAll = 3,
+    ReadClear = 4,
+    Clear = 5,
+    ConsoleOff = 6,
+    ConsoleOn = 7,
+    ConsoleLevel = 8,
+    SizeUnread = 9,
+    SizeBuffer = 10,
+}
@@ -10,17 +10,18 @@ use crate::{
         posix_thread::{thread_table, AsPosixThread},
         process_table,
         signal::{
-            c_types::{sigevent_t, SigNotify},
+            c_types::{sigevent_t, sigval_t, SigNotify},
             constants::SIGALRM,
             sig_num::SigNum,
-            signals::kernel::KernelSignal,
         },
+        TimerNotify,
     },
     syscall::ClockId,
-    thread::work_queue::{submit_work_item, work_item::WorkItem},
+    thread::Thread,
     time::{
         clockid_t,
-        clocks::{BootTimeClock, MonotonicClock, RealTimeClock},
+        clocks::{BootTimeClock, MonotonicClock, RealTimeClock, TaiClock},
+        TimerManager,
     },
 };
 
@@ -37,122 +38,168 @@ pub fn sys_timer_create(
         );
     }
 
-    let current_process = current!();
-    let sent_signal: Box<dyn Fn() + Send + Sync + 'static> = {
-        // If `sigevent_addr` is NULL, use the default method (like `sys_alarm`) to send signal.
-        if sigevent_addr == 0 {
-            let process = current_process.clone();
-            let signal = KernelSignal::new(SIGALRM);
-            Box::new(move || {
-                process.enqueue_signal(signal);
-            })
-        // Determine the timeout action through `sigevent`.
-        } else {
-            let sig_event = ctx.user_space().read_val::<sigevent_t>(sigevent_addr)?;
-            let sigev_notify = SigNotify::try_from(sig_event.sigev_notify)?;
-            let signo = sig_event.sigev_signo;
-            match sigev_notify {
+    let timer_manager = clock_timer_manager(clockid, ctx)?;
+
+    // If `sigevent_addr` is NULL, the timer sends `SIGALRM` to the current process, with the
+    // timer ID as the signal value.
+    let sig_event = if sigevent_addr == 0 {
+        None
+    } else {
+        Some(ctx.user_space().read_val::<sigevent_t>(sigevent_addr)?)
+    };
+    let (target, sig_num) = match &sig_event {
+        None => (Some(TimerTarget::Process), SIGALRM),
+        Some(sig_event) => {
+            let sig_num = u8::try_from(sig_event.sigev_signo)
+                .ok()
+                .and_then(|signo| SigNum::try_from(signo).ok());
+            match SigNotify::try_from(sig_event.sigev_notify)? {
                 // Do nothing when the timer is expired.
-                SigNotify::SIGEV_NONE => Box::new(|| {}),
+                SigNotify::SIGEV_NONE => (None, SIGALRM),
                 // Send a signal to the current process when the timer is expired.
-                SigNotify::SIGEV_SIGNAL => {
-                    let process = current_process.clone();
-                    let signal = KernelSignal::new(SigNum::try_from(signo as u8)?);
-                    Box::new(move || {
-                        process.enqueue_signal(signal);
-                    })
-                }
-                // Spawn a posix thread to run the `sigev_function`, which is stored in
-                // `sig_event.sigev_un._sigev_thread`.
                 //
-                // TODO: enable this instructions. Currently the system does not provide an API to spawn
-                // a posix thread to run a specified function.
-                SigNotify::SIGEV_THREAD => {
-                    unimplemented!()
+                // Like Linux, `SIGEV_THREAD` is treated as `SIGEV_SIGNAL`, since spawning a
+                // thread to run the function is implemented by the C library.
+                SigNotify::SIGEV_SIGNAL | SigNotify::SIGEV_THREAD => {
+                    let Some(sig_num) = sig_num else {
+                        return_errno_with_message!(Errno::EINVAL, "invalid signal number");
+                    };
+                    (Some(TimerTarget::Process), sig_num)
                 }
                 // Send a signal to the specified thread when the timer is expired.
                 SigNotify::SIGEV_THREAD_ID => {
+                    let Some(sig_num) = sig_num else {
+                        return_errno_with_message!(Errno::EINVAL, "invalid signal number");
+                    };
                     let tid = sig_event.sigev_un.read_tid() as u32;
                     let thread = thread_table::get_thread(tid).ok_or_else(|| {
                         Error::with_message(Errno::EINVAL, "target thread does not exist")
                     })?;
                     let posix_thread = thread.as_posix_thread().unwrap();
-                    if posix_thread.process().pid() != current_process.pid() {
+                    if posix_thread.process().pid() != ctx.process.pid() {
                         return_errno_with_message!(
                             Errno::EINVAL,
                             "target thread should belong to current process"
                         );
                     }
-                    let signal = KernelSignal::new(SigNum::try_from(signo as u8)?);
-                    Box::new(move || {
-                        if let Some(thread) = thread.as_posix_thread() {
-                            thread.enqueue_signal(Box::new(signal));
-                        }
-                    })
+                    (Some(TimerTarget::Thread(Arc::downgrade(&thread))), sig_num)
                 }
             }
         }
     };
 
-    let work_func = sent_signal;
-    let work_item = WorkItem::new(work_func);
-    let func = move || {
-        submit_work_item(
-            work_item.clone(),
-            crate::thread::work_queue::WorkPriority::High,
+    let weak_process = ctx.posix_thread.weak_process();
+    let new_notify = |timer_id: usize| {
+        let value = sig_event.map_or_else(
+            || sigval_t::from_int(timer_id as i32),
+            |sig_event| sig_event.sigev_value,
         );
-    };
-
-    let process_timer_manager = current_process.timer_manager();
-    let timer = if clockid >= 0 {
-        let clock_id = ClockId::try_from(clockid)?;
-        match clock_id {
-            ClockId::CLOCK_PROCESS_CPUTIME_ID => process_timer_manager.create_prof_timer(func),
-            ClockId::CLOCK_THREAD_CPUTIME_ID => ctx.posix_thread.create_prof_timer(func),
-            ClockId::CLOCK_REALTIME => RealTimeClock::timer_manager().create_timer(func),
-            ClockId::CLOCK_MONOTONIC => MonotonicClock::timer_manager().create_timer(func),
-            ClockId::CLOCK_BOOTTIME => BootTimeClock::timer_manager().create_timer(func),
-            _ => return_errno_with_message!(Errno::EINVAL, "invalid clock ID"),
-        }
-    } else {
-        let dynamic_clockid_info = DynamicClockIdInfo::try_from(clockid)?;
-        match dynamic_clockid_info {
-            DynamicClockIdInfo::Pid(pid, clock_type) => {
-                let process = process_table::get_process(pid)
-                    .ok_or_else(|| crate::Error::with_message(Errno::EINVAL, "invalid clock id"))?;
-                let process_timer_manager = process.timer_manager();
-                match clock_type {
-                    DynamicClockType::Profiling => process_timer_manager.create_prof_timer(func),
-                    DynamicClockType::Virtual => process_timer_manager.create_virtual_timer(func),
-                    // TODO: support scheduling clock and fd clock.
-                    _ => unimplemented!(),
-                }
-            }
-            DynamicClockIdInfo::Tid(tid, clock_type) => {
-                let thread = thread_table::get_thread(tid)
-                    .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid clock id"))?;
-                let posix_thread = thread.as_posix_thread().unwrap();
-                match clock_type {
-                    DynamicClockType::Profiling => posix_thread.create_prof_timer(func),
-                    DynamicClockType::Virtual => posix_thread.create_virtual_timer(func),
-                    _ => unimplemented!(),
-                }
-            }
-            DynamicClockIdInfo::Fd(_) => unimplemented!(),
+        match target {
+            None => TimerNotify::None,
+            Some(TimerTarget::Process) => TimerNotify::Process(weak_process, sig_num, value),
+            Some(TimerTarget::Thread(thread)) => TimerNotify::Thread(thread, sig_num, value),
         }
     };
 
-    let timer_id = process_timer_manager.add_posix_timer(timer);
-    ctx.user_space().write_val(timer_id_addr, &timer_id)?;
+    let process_timer_manager = ctx.process.timer_manager();
+    let timer_id = process_timer_manager.create_posix_timer(&timer_manager, new_notify)?;
+    if let Err(err) = ctx.user_space().write_val(timer_id_addr, &timer_id) {
+        process_timer_manager.remove_posix_timer(timer_id);
+        return Err(err);
+    }
     Ok(SyscallReturn::Return(0))
 }
 
-pub fn sys_timer_delete(timer_id: usize, _ctx: &Context) -> Result<SyscallReturn> {
-    let current_process = current!();
-    let Some(timer) = current_process.timer_manager().remove_posix_timer(timer_id) else {
+pub fn sys_timer_delete(timer_id: usize, ctx: &Context) -> Result<SyscallReturn> {
+    let Some(posix_timer) = ctx.process.timer_manager().remove_posix_timer(timer_id) else {
         return_errno_with_message!(Errno::EINVAL, "invalid timer ID");
     };
 
-    timer.cancel();
+    posix_timer.timer().cancel();
     Ok(SyscallReturn::Return(0))
 }
+
+pub fn sys_timer_getoverrun(timer_id: usize, ctx: &Context) -> Result<SyscallReturn> {
+    let Some(posix_timer) = ctx.process.timer_manager().find_posix_timer(timer_id) else {
+        return_errno_with_message!(Errno::EINVAL, "invalid timer ID");
+    };
+
+    Ok(SyscallReturn::Return(posix_timer.overrun() as _))
+}
+
+/// The target of the signals sent by a POSIX timer.
+enum TimerTarget {
+    Process,
+    Thread(Weak<Thread>),
+}
+
+/// Returns the timer manager of the clock specified by the clock ID.
+fn clock_timer_manager(clockid: clockid_t, ctx: &Context) -> Result<Arc<TimerManager>> {
+    if clockid >= 0 {
+        let clock_id = ClockId::try_from(clockid)?;
+        let timer_manager = match clock_id {
+            ClockId::CLOCK_PROCESS_CPUTIME_ID => {
+                ctx.process.timer_manager().prof_timer().timer_manager()
+            }
+            ClockId::CLOCK_THREAD_CPUTIME_ID => ctx.posix_thread.prof_timer_manager(),
+            ClockId::CLOCK_REALTIME => RealTimeClock::timer_manager(),
+            ClockId::CLOCK_MONOTONIC => MonotonicClock::timer_manager(),
+            ClockId::CLOCK_BOOTTIME => BootTimeClock::timer_manager(),
+            ClockId::CLOCK_TAI => TaiClock::timer_manager(),
+            // Like Linux, timers are not supported on the raw and coarse clocks. The alarm
+            // clocks are not supported either, since there is no RTC that can wake up the system.
+            ClockId::CLOCK_MONOTONIC_RAW
+            | ClockId::CLOCK_REALTIME_COARSE
+            | ClockId::CLOCK_MONOTONIC_COARSE
+            | ClockId::CLOCK_REALTIME_ALARM
+            | ClockId::CLOCK_BOOTTIME_ALARM => {
+                return_errno_with_message!(Errno::EOPNOTSUPP, "the clock does not support timers")
+            }
+        };
+        return Ok(timer_manager.clone());
+    }
+
+    let dynamic_clockid_info = DynamicClockIdInfo::try_from(clockid)?;
+    match dynamic_clockid_info {
+        DynamicClockIdInfo::Pid(pid, clock_type) => {
+            let process = process_table::get_process(pid)
+                .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid clock id"))?;
+            let process_timer_manager = process.timer_manager();
+            let timer = match clock_type {
+                // The scheduling clock is the same as the profiling clock, since the CPU time is
+                // accounted precisely.
+                DynamicClockType::Profiling | DynamicClockType::Scheduling => {
+                    process_timer_manager.prof_timer()
+                }
+                DynamicClockType::Virtual => process_timer_manager.virtual_timer(),
+                DynamicClockType::FD => {
+                    return_errno_with_message!(Errno::EINVAL, "invalid clock id")
+                }
+            };
+            Ok(timer.timer_manager().clone())
+        }
+        DynamicClockIdInfo::Tid(tid, clock_type) => {
+            let thread = thread_table::get_thread(tid)
+                .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid clock id"))?;
+            let posix_thread = thread.as_posix_thread().unwrap();
+            // Like Linux, timers can only be created on the CPU clocks of the threads in the
+            // current process.
+            if posix_thread.process().pid() != ctx.process.pid() {
+                return_errno_with_message!(Errno::EINVAL, "invalid clock id");
+            }
+            let timer_manager = match clock_type {
+                DynamicClockType::Profiling | DynamicClockType::Scheduling => {
+                    posix_thread.prof_timer_manager()
+                }
+                DynamicClockType::Virtual => posix_thread.virtual_timer_manager(),
+                DynamicClockType::FD => {
+                    return_errno_with_message!(Errno::EINVAL, "invalid clock id")
+                }
+            };
+            Ok(timer_manager.clone())
+        }
+        DynamicClockIdInfo::Fd(_) => {
+            return_errno_with_message!(Errno::EINVAL, "timers on the FD clocks are not supported")
+        }
+    }
+}
@@ -24,9 +24,10 @@ pub fn sys_timer_settime(
     let interval = Duration::try_from(new_itimerspec.it_interval)?;
     let expire_time = Duration::try_from(new_itimerspec.it_value)?;
 
-    let Some(timer) = ctx.process.timer_manager().find_posix_timer(timer_id) else {
+    let Some(posix_timer) = ctx.process.timer_manager().find_posix_timer(timer_id) else {
         return_errno_with_message!(Errno::EINVAL, "invalid timer ID");
     };
+    let timer = posix_timer.timer();
 
     if old_itimerspec_addr > 0 {
         let old_interval = timespec_t::from(timer.interval());
@@ -43,12 +44,11 @@ pub fn sys_timer_settime(
         // Clear previous timer
         timer.cancel();
     } else {
-        let timeout = if flags == 0 {
-            Timeout::After(expire_time)
-        } else if flags == TIMER_ABSTIME {
+        // Like Linux, the flags other than `TIMER_ABSTIME` are ignored.
+        let timeout = if flags & TIMER_ABSTIME != 0 {
             Timeout::When(expire_time)
         } else {
-            unreachable!()
+            Timeout::After(expire_time)
         };
         timer.set_timeout(timeout);
     }
@@ -64,9 +64,10 @@ pub fn sys_timer_gettime(
     if itimerspec_addr == 0 {
         return_errno_with_message!(Errno::EINVAL, "invalid pointer to return value");
     }
-    let Some(timer) = ctx.process.timer_manager().find_posix_timer(timer_id) else {
+    let Some(posix_timer) = ctx.process.timer_manager().find_posix_timer(timer_id) else {
         return_errno_with_message!(Errno::EINVAL, "invalid timer ID");
     };
+    let timer = posix_timer.timer();
 
     let interval = timespec_t::from(timer.interval());
     let remain = timespec_t::from(timer.remain());
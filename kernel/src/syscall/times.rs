@@ -0,0 +1,40 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::SyscallReturn;
+use crate::{
+    prelude::*,
+    time::{clock_t, clocks::MonotonicClock, duration_to_clock_t},
+};
+
+pub fn sys_times(tms_addr: Vaddr, ctx: &Context) -> Result<SyscallReturn> {
+    debug!("tms_addr = 0x{:x}", tms_addr);
+
+    if tms_addr != 0 {
+        let prof_clock = ctx.process.prof_clock();
+        let children_prof_clock = ctx.process.children_prof_clock();
+        let tms = tms_t {
+            tms_utime: duration_to_clock_t(prof_clock.user_clock().read_time()),
+            tms_stime: duration_to_clock_t(prof_clock.kernel_clock().read_time()),
+            tms_cutime: duration_to_clock_t(children_prof_clock.user_clock().read_time()),
+            tms_cstime: duration_to_clock_t(children_prof_clock.kernel_clock().read_time()),
+        };
+        ctx.user_space().write_val(tms_addr, &tms)?;
+    }
+
+    // Like Linux, the return value is the number of clock ticks since the system boot.
+    let ticks = duration_to_clock_t(MonotonicClock::get().read_time());
+    Ok(SyscallReturn::Return(ticks as _))
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, Pod)]
+struct tms_t {
+    /// user time
+    tms_utime: clock_t,
+    /// system time
+    tms_stime: clock_t,
+    /// user time of children
+    tms_cutime: clock_t,
+    /// system time of children
+    tms_cstime: clock_t,
+}
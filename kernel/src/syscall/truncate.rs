@@ -40,6 +40,28 @@ pub fn sys_truncate(path_ptr: Vaddr, len: isize, ctx: &Context) -> Result<Syscal
     Ok(SyscallReturn::Return(0))
 }
 
+/// The `ftruncate64` system call of 32-bit programs, whose length is split into two registers.
+pub fn compat_sys_ftruncate64(
+    fd: FileDesc,
+    len_low: u32,
+    len_high: u32,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let len = ((len_high as u64) << 32 | len_low as u64) as isize;
+    sys_ftruncate(fd, len, ctx)
+}
+
+/// The `truncate64` system call of 32-bit programs, whose length is split into two registers.
+pub fn compat_sys_truncate64(
+    path_ptr: Vaddr,
+    len_low: u32,
+    len_high: u32,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let len = ((len_high as u64) << 32 | len_low as u64) as isize;
+    sys_truncate(path_ptr, len, ctx)
+}
+
 #[inline]
 fn check_length(len: isize, ctx: &Context) -> Result<()> {
     if len < 0 {
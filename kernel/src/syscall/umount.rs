@@ -4,6 +4,7 @@ use super::SyscallReturn;
 use crate::{
     fs::fs_resolver::{FsPath, AT_FDCWD},
     prelude::*,
+    security::hooks,
     syscall::constants::MAX_FILENAME_LEN,
 };
 
@@ -30,6 +31,7 @@ pub fn sys_umount(path_addr: Vaddr, flags: u64, ctx: &Context) -> Result<Syscall
         ctx.posix_thread.fs().resolver().read().lookup(&fs_path)?
     };
 
+    hooks::sb_umount(&target_dentry)?;
     target_dentry.unmount()?;
 
     Ok(SyscallReturn::Return(0))
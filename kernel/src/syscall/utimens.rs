@@ -110,6 +110,7 @@ struct Utimbuf {
 }
 
 fn vfs_utimes(dentry: &Dentry, times: Option<TimeSpecPair>) -> Result<SyscallReturn> {
+    dentry.check_mount_writable()?;
     let (atime, mtime, ctime) = match times {
         Some(times) => {
             if !times.atime.is_valid() || !times.mtime.is_valid() {
@@ -0,0 +1,56 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{splice::SpliceFlags, SyscallReturn};
+use crate::{
+    fs::{
+        file_table::{get_file_fast, FileDesc},
+        pipe::{PipeReader, PipeWriter},
+    },
+    prelude::*,
+    util::{VmReaderArray, VmWriterArray},
+};
+
+/// The maximum number of I/O vectors.
+const UIO_MAXIOV: usize = 1024;
+
+pub fn sys_vmsplice(
+    fd: FileDesc,
+    io_vec_ptr: Vaddr,
+    io_vec_count: usize,
+    flags: u32,
+    ctx: &Context,
+) -> Result<SyscallReturn> {
+    let flags = SpliceFlags::from_bits(flags)
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "unknown flags"))?;
+    debug!(
+        "fd = {}, io_vec_ptr = 0x{:x}, io_vec_count = {}, flags = {:?}",
+        fd, io_vec_ptr, io_vec_count, flags
+    );
+
+    if io_vec_count > UIO_MAXIOV {
+        return_errno_with_message!(Errno::EINVAL, "too many I/O vectors");
+    }
+
+    let mut file_table = ctx.thread_local.file_table().borrow_mut();
+    let file = get_file_fast!(&mut file_table, fd);
+
+    // Unlike `splice()`, the `O_NONBLOCK` flag of the pipe is not taken into account.
+    let is_nonblocking = flags.contains(SpliceFlags::SPLICE_F_NONBLOCK);
+    let user_space = ctx.user_space();
+
+    // The pages of the user memory are copied instead of being mapped into the pipe, so
+    // `SPLICE_F_GIFT` has no effect.
+    let spliced_len = if let Some(writer) = file.downcast_ref::<PipeWriter>() {
+        let mut reader_array =
+            VmReaderArray::from_user_io_vecs(&user_space, io_vec_ptr, io_vec_count)?;
+        writer.write_vectored(&mut reader_array, is_nonblocking)?
+    } else if let Some(reader) = file.downcast_ref::<PipeReader>() {
+        let mut writer_array =
+            VmWriterArray::from_user_io_vecs(&user_space, io_vec_ptr, io_vec_count)?;
+        reader.read_vectored(&mut writer_array, is_nonblocking)?
+    } else {
+        return_errno_with_message!(Errno::EBADF, "the file is not a pipe");
+    };
+
+    Ok(SyscallReturn::Return(spliced_len as _))
+}
@@ -38,11 +38,9 @@ pub fn sys_wait4(
     }
 
     if rusage_addr != 0 {
-        let rusage = rusage_t {
-            ru_utime: process.prof_clock().user_clock().read_time().into(),
-            ru_stime: process.prof_clock().kernel_clock().read_time().into(),
-            ..Default::default()
-        };
+        // Like Linux, the CPU time includes that of the reaped descendants of the child.
+        let rusage =
+            rusage_t::from_prof_clocks(&[process.prof_clock(), process.children_prof_clock()]);
 
         ctx.user_space().write_val(rusage_addr, &rusage)?;
     }
@@ -6,9 +6,10 @@ use aster_rights::Full;
 use ostd::cpu::context::{CpuExceptionInfo, UserContext};
 
 use crate::{
-    current_userspace,
+    current_userspace, perf,
     prelude::*,
     process::signal::signals::fault::FaultSignal,
+    trace::PAGE_FAULT_USER,
     vm::{page_fault_handler::PageFaultHandler, perms::VmPerms, vmar::Vmar},
 };
 
@@ -32,6 +33,7 @@ pub fn handle_exception(ctx: &Context, context: &UserContext) {
     log_trap_info(trap_info);
 
     if let Ok(page_fault_info) = PageFaultInfo::try_from(trap_info) {
+        perf::on_page_fault(ctx);
         let user_space = ctx.user_space();
         let root_vmar = user_space.root_vmar();
         if handle_page_fault_from_vmar(root_vmar, &page_fault_info).is_ok() {
@@ -47,6 +49,10 @@ fn handle_page_fault_from_vmar(
     root_vmar: &Vmar<Full>,
     page_fault_info: &PageFaultInfo,
 ) -> core::result::Result<(), ()> {
+    PAGE_FAULT_USER.emit(&[
+        page_fault_info.address as u64,
+        page_fault_info.required_perms.bits() as u64,
+    ]);
     if let Err(e) = root_vmar.handle_page_fault(page_fault_info) {
         warn!(
             "page fault handler failed: addr: 0x{:x}, err: {:?}",
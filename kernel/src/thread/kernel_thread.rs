@@ -8,7 +8,7 @@ use ostd::{
 use super::{oops, AsThread, Thread};
 use crate::{
     prelude::*,
-    sched::{Nice, SchedPolicy},
+    sched::{Nice, RealTimePolicy, RealTimePriority, SchedPolicy},
 };
 
 /// The inner data of a kernel thread.
@@ -85,3 +85,16 @@ impl ThreadOptions {
         thread
     }
 }
+
+/// Spawns a kernel thread to run a threaded IRQ handler.
+///
+/// The thread uses the FIFO policy with the middle real-time priority, as Linux does, so that
+/// the handler runs before the normal threads.
+pub(super) fn spawn_irq_thread(func: Box<dyn FnOnce() + Send>) {
+    ThreadOptions::new(func)
+        .sched_policy(SchedPolicy::RealTime {
+            rt_prio: RealTimePriority::new(50),
+            rt_policy: RealTimePolicy::Fifo,
+        })
+        .spawn();
+}
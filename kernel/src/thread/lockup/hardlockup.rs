@@ -0,0 +1,82 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The hard lockup detector.
+//!
+//! A CPU is hard-locked-up if it keeps running with the local IRQs disabled, so that even the
+//! heartbeat of the CPU itself cannot run. Like Linux's buddy detector, each CPU watches the
+//! next CPU. It probes the buddy with an inter-processor call at each heartbeat, and reports
+//! a hard lockup if the probe is not answered within the threshold.
+//!
+//! This requires at least two CPUs, as well as inter-processor interrupts.
+
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use ostd::cpu::{num_cpus, CpuId, CpuSet, PinCurrentCpu};
+
+use super::{cpu_state, dump_cpu, WATCHDOG_THRESH};
+use crate::prelude::*;
+
+/// The threshold (in jiffies) to report a hard lockup.
+const HARDLOCKUP_THRESHOLD: u64 = WATCHDOG_THRESH;
+
+/// The hard lockup state of a CPU, which is updated by its buddy.
+pub(super) struct HardLockupState {
+    /// Whether a probe is sent to the CPU but not answered yet
+    is_probing: AtomicBool,
+    /// The timestamp (in jiffies) when the pending probe was sent
+    probe_ts: AtomicU64,
+    /// Whether the current hard lockup has been reported
+    has_reported: AtomicBool,
+}
+
+impl HardLockupState {
+    pub(super) fn new() -> Self {
+        Self {
+            is_probing: AtomicBool::new(false),
+            probe_ts: AtomicU64::new(0),
+            has_reported: AtomicBool::new(false),
+        }
+    }
+}
+
+/// Probes the buddy of the current CPU, and checks whether the buddy is hard-locked-up.
+pub(super) fn check(cpu_id: CpuId, now: u64) {
+    let num_cpus = num_cpus();
+    if num_cpus < 2 {
+        return;
+    }
+    let buddy = CpuId::try_from((cpu_id.as_usize() + 1) % num_cpus).unwrap();
+    let state = &cpu_state(buddy).hard;
+
+    if !state.is_probing.load(Ordering::Acquire) {
+        // The last probe has been answered, so send a new one.
+        state.has_reported.store(false, Ordering::Relaxed);
+        state.probe_ts.store(now, Ordering::Relaxed);
+        state.is_probing.store(true, Ordering::Release);
+
+        let mut cpu_set = CpuSet::new_empty();
+        cpu_set.add(buddy);
+        ostd::smp::inter_processor_call(&cpu_set, answer_probe);
+        return;
+    }
+
+    let stuck_for = now.saturating_sub(state.probe_ts.load(Ordering::Relaxed));
+    if stuck_for < HARDLOCKUP_THRESHOLD || state.has_reported.swap(true, Ordering::Relaxed) {
+        return;
+    }
+
+    error!(
+        "Watchdog detected hard LOCKUP on CPU#{} (reported by CPU#{})",
+        buddy.as_usize(),
+        cpu_id.as_usize()
+    );
+    dump_cpu(buddy);
+}
+
+fn answer_probe() {
+    let cpu_id = ostd::trap::disable_local().current_cpu();
+    cpu_state(cpu_id)
+        .hard
+        .is_probing
+        .store(false, Ordering::Release);
+}
@@ -0,0 +1,106 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The lockup detectors.
+//!
+//! The detectors are driven by a per-CPU heartbeat in the timer interrupt handler:
+//!
+//! - The soft lockup detector reports a CPU that keeps running in the kernel without
+//!   scheduling other threads (see [`softlockup`]).
+//! - The hard lockup detector reports a CPU that keeps running with the local IRQs disabled.
+//!   Each CPU probes its buddy CPU with inter-processor calls, which cannot be answered
+//!   if the buddy does not take interrupts (see [`hardlockup`]).
+//! - The RCU stall detector reports the CPUs that do not pass a quiescent state in an RCU
+//!   grace period for too long (see [`rcu_stall`]).
+//!
+//! On a report, the registers and the stack trace of the offending CPU are printed. If the
+//! offending CPU is not the current one, it is asked to print them by an inter-processor call,
+//! which is done as soon as the CPU takes interrupts again.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use ostd::{
+    arch::timer::TIMER_FREQ,
+    cpu::{num_cpus, CpuId, CpuSet, PinCurrentCpu},
+    task::disable_preempt,
+    timer::{self, Jiffies},
+};
+use spin::Once;
+
+use self::{hardlockup::HardLockupState, softlockup::SoftLockupState};
+use crate::prelude::*;
+
+mod hardlockup;
+mod rcu_stall;
+mod softlockup;
+
+/// The threshold (in jiffies) to report a hard lockup.
+///
+/// The thresholds of the other detectors are derived from it, like Linux's `watchdog_thresh`.
+const WATCHDOG_THRESH: u64 = 10 * TIMER_FREQ;
+
+/// The period (in jiffies) between two heartbeats that check the lockups.
+const SAMPLE_PERIOD: u64 = softlockup::SOFTLOCKUP_THRESHOLD / 5;
+
+static CPU_STATES: Once<Vec<CpuState>> = Once::new();
+
+/// The lockup detection state of a CPU.
+struct CpuState {
+    /// The timestamp (in jiffies) of the last heartbeat
+    last_sample: AtomicU64,
+    soft: SoftLockupState,
+    hard: HardLockupState,
+}
+
+impl CpuState {
+    fn new() -> Self {
+        Self {
+            last_sample: AtomicU64::new(Jiffies::elapsed().as_u64()),
+            soft: SoftLockupState::new(),
+            hard: HardLockupState::new(),
+        }
+    }
+}
+
+fn cpu_state(cpu_id: CpuId) -> &'static CpuState {
+    &CPU_STATES.get().unwrap()[cpu_id.as_usize()]
+}
+
+/// Starts the lockup detectors on the current CPU.
+///
+/// This function should be called once on each CPU.
+pub(crate) fn init_on_cpu() {
+    CPU_STATES.call_once(|| (0..num_cpus()).map(|_| CpuState::new()).collect());
+
+    let cpu_id = disable_preempt().current_cpu();
+    let state = cpu_state(cpu_id);
+    state
+        .last_sample
+        .store(Jiffies::elapsed().as_u64(), Ordering::Relaxed);
+
+    softlockup::init_on_cpu(cpu_id, &state.soft);
+    timer::register_callback(move || heartbeat(cpu_id));
+}
+
+fn heartbeat(cpu_id: CpuId) {
+    let state = cpu_state(cpu_id);
+    let now = Jiffies::elapsed().as_u64();
+    if now < state.last_sample.load(Ordering::Relaxed) + SAMPLE_PERIOD {
+        return;
+    }
+    state.last_sample.store(now, Ordering::Relaxed);
+
+    softlockup::check(cpu_id, &state.soft, now);
+    hardlockup::check(cpu_id, now);
+    rcu_stall::check(now);
+}
+
+/// Prints the registers and the stack trace of the CPU.
+fn dump_cpu(cpu_id: CpuId) {
+    fn dump_current_cpu() {
+        ostd::trap::dump_interrupted_context();
+    }
+
+    let mut cpu_set = CpuSet::new_empty();
+    cpu_set.add(cpu_id);
+    ostd::smp::inter_processor_call(&cpu_set, dump_current_cpu);
+}
@@ -0,0 +1,55 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The RCU stall detector.
+//!
+//! An RCU grace period stalls if some CPUs do not pass a quiescent state (i.e., do not
+//! schedule) for a long time, which delays the reclamation of all RCU-protected data.
+//! The detector reports each stalled grace period once, along with the stalling CPUs.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use ostd::sync::current_grace_period;
+
+use super::{dump_cpu, WATCHDOG_THRESH};
+use crate::prelude::*;
+
+/// The threshold (in jiffies) to report an RCU stall, which is 21 seconds by default in Linux.
+const RCU_STALL_THRESHOLD: u64 = 2 * WATCHDOG_THRESH + WATCHDOG_THRESH / 10;
+
+/// The sequence number of the last reported grace period.
+static REPORTED_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// Checks whether the current grace period stalls.
+pub(super) fn check(now: u64) {
+    let Some(gp) = current_grace_period() else {
+        return;
+    };
+
+    let stalled_for = now.saturating_sub(gp.start.as_u64());
+    if stalled_for < RCU_STALL_THRESHOLD {
+        return;
+    }
+    // Report the grace period on only one CPU.
+    let reported_seq = REPORTED_SEQ.load(Ordering::Relaxed);
+    if reported_seq >= gp.seq
+        || REPORTED_SEQ
+            .compare_exchange(reported_seq, gp.seq, Ordering::Relaxed, Ordering::Relaxed)
+            .is_err()
+    {
+        return;
+    }
+
+    error!(
+        "INFO: rcu detected stalls on {} CPU(s) (t={} jiffies, grace period {})",
+        gp.pending_cpus.count(),
+        stalled_for,
+        gp.seq
+    );
+    for cpu_id in gp.pending_cpus.iter() {
+        error!(
+            "rcu:     CPU#{} has not passed a quiescent state",
+            cpu_id.as_usize()
+        );
+        dump_cpu(cpu_id);
+    }
+}
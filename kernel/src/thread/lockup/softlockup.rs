@@ -0,0 +1,99 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The soft lockup detector.
+//!
+//! A CPU is soft-locked-up if it keeps running in the kernel without scheduling other
+//! threads. Like Linux, each CPU runs a high-priority watchdog thread that touches a
+//! timestamp whenever it gets scheduled. The heartbeat wakes up the thread periodically
+//! and reports a soft lockup if the timestamp is not touched within the threshold.
+
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use ostd::{cpu::CpuId, sync::WaitQueue, task::Task, timer::Jiffies};
+
+use super::{cpu_state, WATCHDOG_THRESH};
+use crate::{
+    prelude::*,
+    process::posix_thread::AsPosixThread,
+    sched::{RealTimePolicy, RealTimePriority, SchedPolicy},
+    thread::kernel_thread::ThreadOptions,
+};
+
+/// The threshold (in jiffies) to report a soft lockup.
+pub(super) const SOFTLOCKUP_THRESHOLD: u64 = 2 * WATCHDOG_THRESH;
+
+/// The soft lockup state of a CPU.
+pub(super) struct SoftLockupState {
+    /// The timestamp (in jiffies) touched by the watchdog thread
+    touch_ts: AtomicU64,
+    /// Whether the current soft lockup has been reported
+    has_reported: AtomicBool,
+    /// Whether the watchdog thread should touch the timestamp
+    should_run: AtomicBool,
+    wait_queue: WaitQueue,
+}
+
+impl SoftLockupState {
+    pub(super) fn new() -> Self {
+        Self {
+            touch_ts: AtomicU64::new(Jiffies::elapsed().as_u64()),
+            has_reported: AtomicBool::new(false),
+            should_run: AtomicBool::new(false),
+            wait_queue: WaitQueue::new(),
+        }
+    }
+}
+
+/// Spawns the watchdog thread of the current CPU.
+pub(super) fn init_on_cpu(cpu_id: CpuId, state: &SoftLockupState) {
+    state
+        .touch_ts
+        .store(Jiffies::elapsed().as_u64(), Ordering::Relaxed);
+
+    ThreadOptions::new(move || watchdog_thread(cpu_id))
+        .cpu_affinity(cpu_id.into())
+        .sched_policy(SchedPolicy::RealTime {
+            rt_prio: RealTimePriority::MIN,
+            rt_policy: RealTimePolicy::Fifo,
+        })
+        .spawn();
+}
+
+fn watchdog_thread(cpu_id: CpuId) {
+    let state = &cpu_state(cpu_id).soft;
+    loop {
+        state.wait_queue.wait_until(|| {
+            state
+                .should_run
+                .swap(false, Ordering::Relaxed)
+                .then_some(())
+        });
+        state
+            .touch_ts
+            .store(Jiffies::elapsed().as_u64(), Ordering::Relaxed);
+        state.has_reported.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Wakes up the watchdog thread, and checks whether the current CPU is soft-locked-up.
+pub(super) fn check(cpu_id: CpuId, state: &SoftLockupState, now: u64) {
+    state.should_run.store(true, Ordering::Relaxed);
+    state.wait_queue.wake_all();
+
+    let stuck_for = now.saturating_sub(state.touch_ts.load(Ordering::Relaxed));
+    if stuck_for < SOFTLOCKUP_THRESHOLD || state.has_reported.swap(true, Ordering::Relaxed) {
+        return;
+    }
+
+    let tid = Task::current()
+        .and_then(|task| task.as_posix_thread().map(|thread| thread.tid()))
+        .unwrap_or(0);
+    error!(
+        "BUG: soft lockup - CPU#{} stuck for {}s! [tid: {}]",
+        cpu_id.as_usize(),
+        Jiffies::new(stuck_for).as_duration().as_secs(),
+        tid
+    );
+    // The heartbeat runs on the locked-up CPU, so the interrupted context is the culprit.
+    ostd::trap::dump_interrupted_context();
+}
@@ -11,12 +11,16 @@ use ostd::{
 
 use self::status::{AtomicThreadStatus, ThreadStatus};
 use crate::{
+    perf,
     prelude::*,
+    process::posix_thread::account_cpu_time_at_switch,
     sched::{SchedAttr, SchedPolicy},
+    trace::trace_sched_switch,
 };
 
 pub mod exception;
 pub mod kernel_thread;
+pub(crate) mod lockup;
 pub mod oops;
 pub mod status;
 pub mod task;
@@ -26,9 +30,14 @@ pub type Tid = u32;
 
 fn post_schedule_handler() {
     let task = Task::current().unwrap();
+    account_cpu_time_at_switch(task.as_thread());
+    trace_sched_switch(task.as_thread());
+    perf::on_sched_switch(task.as_thread());
+
     let Some(thread_local) = task.as_thread_local() else {
         return;
     };
+    thread_local.rseq().notify_preempted();
 
     let root_vmar = thread_local.root_vmar().borrow();
     if let Some(vmar) = root_vmar.as_ref() {
@@ -38,6 +47,7 @@ fn post_schedule_handler() {
 
 pub(super) fn init() {
     ostd::task::inject_post_schedule_handler(post_schedule_handler);
+    ostd::trap::inject_irq_thread_spawner(kernel_thread::spawn_irq_thread);
     #[cfg(target_arch="x86_64")]
     ostd::arch::trap::inject_user_page_fault_handler(exception::page_fault_handler);
 }
@@ -22,15 +22,24 @@ use core::{
     sync::atomic::{AtomicBool, AtomicUsize, Ordering},
 };
 
+use aster_logger::{LogEntry, LogReadError};
 use ostd::{cpu::PinCurrentCpu, panic, task::disable_preempt};
 
 use super::Thread;
 
 // TODO: Control the kernel commandline parsing from the kernel crate.
-// In Linux it can be dynamically changed by writing to
-// `/proc/sys/kernel/panic`.
 static PANIC_ON_OOPS: AtomicBool = AtomicBool::new(true);
 
+/// Returns whether the kernel panics on oops.
+pub fn panic_on_oops() -> bool {
+    PANIC_ON_OOPS.load(Ordering::Relaxed)
+}
+
+/// Sets whether the kernel panics on oops, i.e., `/proc/sys/kernel/panic_on_oops`.
+pub fn set_panic_on_oops(panic_on_oops: bool) {
+    PANIC_ON_OOPS.store(panic_on_oops, Ordering::Relaxed);
+}
+
 /// The kernel "oops" information.
 pub struct OopsInfo {
     /// The "oops" message.
@@ -59,6 +68,8 @@ where
             let info = err.downcast::<OopsInfo>().unwrap();
 
             log::error!("Oops! {}", info.message);
+            #[cfg(target_arch = "riscv64")]
+            crate::pstore::dump(crate::pstore::DumpReason::Oops, &info.message);
 
             let count = OOPS_COUNT.fetch_add(1, Ordering::Relaxed);
             if count >= MAX_OOPS_COUNT {
@@ -84,8 +95,7 @@ fn panic_handler(info: &core::panic::PanicInfo) -> ! {
     let message = info.message();
 
     if let Some(thread) = Thread::current() {
-        let panic_on_oops = PANIC_ON_OOPS.load(Ordering::Relaxed);
-        if !panic_on_oops && info.can_unwind() {
+        if !panic_on_oops() && info.can_unwind() {
             // TODO: eliminate the need for heap allocation.
             let message = if let Some(location) = info.location() {
                 format!("{} at {}:{}", message, location.file(), location.line())
@@ -101,6 +111,14 @@ fn panic_handler(info: &core::panic::PanicInfo) -> ! {
     let thread = Thread::current();
     let cpu = preempt_guard.current_cpu();
 
+    // Stop the other CPUs, so that the panic screen is not interleaved with their outputs.
+    let _irq_guard = ostd::trap::disable_local();
+    if !panic::enter_panic() {
+        ostd::early_println!("The panic handler panicked: {}", message);
+        panic::abort();
+    }
+    panic::print_banner(format_args!("Kernel panic on CPU {}", cpu.as_usize()));
+
     // Halt the system if the panic is not caught.
     if let Some(location) = info.location() {
         log::error!(
@@ -121,10 +139,62 @@ fn panic_handler(info: &core::panic::PanicInfo) -> ! {
     }
 
     if info.can_unwind() {
+        panic::print_registers();
         panic::print_stack_trace();
     } else {
         log::error!("Backtrace is disabled.");
     }
 
+    replay_logs();
+
+    // Save the kernel logs so that they can be read after the reboot.
+    #[cfg(target_arch = "riscv64")]
+    crate::pstore::dump(crate::pstore::DumpReason::Panic, &message);
+
+    // Boot the capture kernel to dump the memory, if it is loaded.
+    #[cfg(target_arch = "riscv64")]
+    ostd::arch::kexec::crash_kexec();
+
+    panic::print_banner(format_args!("end Kernel panic"));
     panic::abort();
 }
+
+/// The number of the latest kernel messages that are replayed when the kernel panics.
+const NR_REPLAYED_LOGS: u64 = 32;
+
+/// Prints the latest kernel messages to the console.
+///
+/// The messages below the console log level are also printed, since they may tell what
+/// leads to the panic.
+fn replay_logs() {
+    let next_seq = aster_logger::next_seq();
+    let mut seq = aster_logger::first_seq().max(next_seq.saturating_sub(NR_REPLAYED_LOGS));
+
+    ostd::early_println!("Last {} kernel messages:", next_seq - seq);
+    while seq < next_seq {
+        match aster_logger::read_entry(seq) {
+            Ok(entry) => {
+                print_log_entry(&entry);
+                seq += 1;
+            }
+            Err(LogReadError::Lost(first_seq)) => seq = first_seq.max(seq + 1),
+            Err(LogReadError::NotYet) => seq += 1,
+        }
+    }
+}
+
+/// Prints a log entry, whose color follows the colors of the log levels on the console.
+fn print_log_entry(entry: &LogEntry) {
+    let color = match entry.level() {
+        0..=3 => "31",
+        4 => "93",
+        5 | 6 => "34",
+        _ => "92",
+    };
+    ostd::early_println!(
+        "\x1b[32m[{:>10.3}]\x1b[0m \x1b[{}m{}\x1b[0m",
+        entry.timestamp.as_secs_f64(),
+        color,
+        entry.text
+    );
+}
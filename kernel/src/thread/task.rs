@@ -9,7 +9,7 @@ use ostd::{
 use super::{oops, Thread};
 use crate::{
     cpu::LinuxAbi,
-    current_userspace,
+    current_userspace, perf, power,
     prelude::*,
     process::{
         posix_thread::{AsPosixThread, AsThreadLocal, ThreadLocal},
@@ -71,8 +71,18 @@ pub fn create_new_user_task(
             task: &current_task,
         };
 
+        let cpu_time_account = current_posix_thread.cpu_time_account();
         loop {
+            // Restart the restartable sequence if the thread has been switched out in it.
+            current_thread_local
+                .rseq()
+                .handle_return_to_user(&ctx, user_mode.context_mut());
+            if current_thread.is_exited() {
+                break;
+            }
+            cpu_time_account.enter_user_mode(current_posix_thread, &current_process);
             let return_reason = user_mode.execute(has_kernel_event_fn);
+            cpu_time_account.leave_user_mode(current_posix_thread, &current_process);
             let user_ctx = user_mode.context_mut();
             let mut syscall_number = None;
             // handle user event:
@@ -84,6 +94,7 @@ pub fn create_new_user_task(
                 }
                 ReturnReason::KernelEvent => {}
             };
+            perf::take_samples(&ctx, user_ctx);
 
             if current_thread.is_exited() {
                 break;
@@ -99,6 +110,7 @@ pub fn create_new_user_task(
                 debug!("exit due to signal");
                 break;
             }
+            power::try_to_freeze();
         }
         debug!("exit user loop");
     }
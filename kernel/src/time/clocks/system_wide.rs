@@ -3,14 +3,11 @@
 use alloc::sync::Arc;
 use core::time::Duration;
 
-use aster_time::read_monotonic_time;
 use ostd::{cpu::PinCurrentCpu, cpu_local, sync::SpinLock, task::disable_preempt, timer::Jiffies};
 use paste::paste;
 use spin::Once;
 
-use crate::time::{
-    self, system_time::START_TIME_AS_DURATION, timer::TimerManager, Clock, SystemTime,
-};
+use crate::time::{self, timekeeping, timer::TimerManager, Clock};
 
 /// The Clock that reads the jiffies, and turn the counter into `Duration`.
 pub struct JiffiesClock {
@@ -88,7 +85,7 @@ impl RealTimeCoarseClock {
 
 /// `MonotonicCoarseClock` is a coarse-grained version of the monotonic clock.
 ///
-/// This clock is based on [`RealTimeCoarseClock`].
+/// This clock is updated together with [`RealTimeCoarseClock`].
 ///
 /// Usually it will not be used to create a timer.
 pub struct MonotonicCoarseClock {
@@ -96,6 +93,13 @@ pub struct MonotonicCoarseClock {
 }
 
 impl MonotonicCoarseClock {
+    /// A reference to the current value of this clock.
+    fn current_ref() -> &'static Once<SpinLock<Duration>> {
+        static CURRENT: Once<SpinLock<Duration>> = Once::new();
+
+        &CURRENT
+    }
+
     /// Get the singleton of this clock.
     pub fn get() -> &'static Arc<MonotonicCoarseClock> {
         CLOCK_MONOTONIC_COARSE_INSTANCE.get().unwrap()
@@ -104,9 +108,6 @@ impl MonotonicCoarseClock {
 
 /// `MonotonicRawClock` provides raw monotonic time that is not influenced by
 /// NTP corrections.
-///
-/// Note: Currently we have not implement NTP corrections so we treat this clock
-/// as the [`MonotonicClock`].
 pub struct MonotonicRawClock {
     _private: (),
 }
@@ -120,9 +121,6 @@ impl MonotonicRawClock {
 
 /// `BootTimeClock` measures the time elapsed since the system was booted,
 /// including time when the system was suspended.
-///
-/// Note: currently the system will not be suspended so we treat this clock
-/// as the [`MonotonicClock`].
 pub struct BootTimeClock {
     _private: (),
 }
@@ -143,6 +141,28 @@ impl BootTimeClock {
     }
 }
 
+/// `TaiClock` provides the International Atomic Time (TAI), which is the real
+/// time plus the TAI offset set by NTP clients.
+pub struct TaiClock {
+    _private: (),
+}
+
+impl TaiClock {
+    /// Get the singleton of this clock.
+    pub fn get() -> &'static Arc<TaiClock> {
+        CLOCK_TAI_INSTANCE.get().unwrap()
+    }
+
+    /// Get the cpu-local system-wide `TimerManager` singleton of this clock.
+    pub fn timer_manager() -> &'static Arc<TimerManager> {
+        let preempt_guard = disable_preempt();
+        CLOCK_TAI_MANAGER
+            .get_on_cpu(preempt_guard.current_cpu())
+            .get()
+            .unwrap()
+    }
+}
+
 impl Clock for JiffiesClock {
     fn read_time(&self) -> Duration {
         Jiffies::elapsed().as_duration()
@@ -151,15 +171,13 @@ impl Clock for JiffiesClock {
 
 impl Clock for RealTimeClock {
     fn read_time(&self) -> Duration {
-        SystemTime::now()
-            .duration_since(&SystemTime::UNIX_EPOCH)
-            .unwrap()
+        timekeeping::real_time()
     }
 }
 
 impl Clock for MonotonicClock {
     fn read_time(&self) -> Duration {
-        read_monotonic_time()
+        timekeeping::monotonic_time()
     }
 }
 
@@ -171,19 +189,25 @@ impl Clock for RealTimeCoarseClock {
 
 impl Clock for MonotonicCoarseClock {
     fn read_time(&self) -> Duration {
-        RealTimeCoarseClock::get().read_time() - *START_TIME_AS_DURATION.get().unwrap()
+        *Self::current_ref().get().unwrap().disable_irq().lock()
     }
 }
 
 impl Clock for MonotonicRawClock {
     fn read_time(&self) -> Duration {
-        read_monotonic_time()
+        timekeeping::monotonic_raw_time()
     }
 }
 
 impl Clock for BootTimeClock {
     fn read_time(&self) -> Duration {
-        read_monotonic_time()
+        timekeeping::boot_time()
+    }
+}
+
+impl Clock for TaiClock {
+    fn read_time(&self) -> Duration {
+        timekeeping::tai_time()
     }
 }
 
@@ -251,9 +275,10 @@ define_system_clocks! {
     CLOCK_MONOTONIC_COARSE  => MonotonicCoarseClock,
     CLOCK_MONOTONIC_RAW     => MonotonicRawClock,
     CLOCK_BOOTTIME          => BootTimeClock,
+    CLOCK_TAI               => TaiClock,
 }
 
-define_timer_managers![CLOCK_REALTIME, CLOCK_MONOTONIC, CLOCK_BOOTTIME,];
+define_timer_managers![CLOCK_REALTIME, CLOCK_MONOTONIC, CLOCK_BOOTTIME, CLOCK_TAI,];
 
 /// Init the system-wide clocks.
 fn init_system_wide_clocks() {
@@ -283,14 +308,17 @@ fn init_jiffies_clock_manager() {
 }
 
 fn update_coarse_clock() {
-    let real_time = RealTimeClock::get().read_time();
+    let now = timekeeping::adjusted_at(timekeeping::monotonic_raw_time());
     let current = RealTimeCoarseClock::current_ref().get().unwrap();
-    *current.disable_irq().lock() = real_time;
+    *current.disable_irq().lock() = now.real;
+    let current = MonotonicCoarseClock::current_ref().get().unwrap();
+    *current.disable_irq().lock() = now.monotonic;
 }
 
 fn init_coarse_clock() {
-    let real_time = RealTimeClock::get().read_time();
-    RealTimeCoarseClock::current_ref().call_once(|| SpinLock::new(real_time));
+    let now = timekeeping::adjusted_at(timekeeping::monotonic_raw_time());
+    RealTimeCoarseClock::current_ref().call_once(|| SpinLock::new(now.real));
+    MonotonicCoarseClock::current_ref().call_once(|| SpinLock::new(now.monotonic));
     time::softirq::register_callback(update_coarse_clock);
 }
 
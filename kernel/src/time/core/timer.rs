@@ -86,7 +86,7 @@ impl Timer {
         let timer_weak = Arc::downgrade(self);
         let new_timer_callback = Arc::new(TimerCallback::new(
             expired_time,
-            Box::new(move || interval_timer_callback(&timer_weak)),
+            Box::new(move || interval_timer_callback(&timer_weak, expired_time)),
         ));
 
         let mut timer_callback = self.timer_callback.disable_irq().lock();
@@ -127,17 +127,44 @@ impl Timer {
     pub fn interval(&self) -> Duration {
         *self.interval.disable_irq().lock()
     }
+
+    /// Returns the number of the expirations that have been missed since the current expired
+    /// time of this timer.
+    ///
+    /// The timer callbacks may be invoked later than the expired time, e.g., the timers based on
+    /// the CPU clocks are only checked at the timer interrupts. If the interval of the timer is
+    /// shorter than the delay, some expirations are missed.
+    pub fn missed_expirations(&self) -> u64 {
+        self.missed_expirations_since(self.expired_time())
+    }
+
+    fn missed_expirations_since(&self, expired_time: Duration) -> u64 {
+        let interval = self.interval().as_nanos();
+        if interval == 0 {
+            return 0;
+        }
+
+        let now = self.timer_manager.clock.read_time();
+        let delay = now.saturating_sub(expired_time).as_nanos();
+        (delay / interval) as u64
+    }
 }
 
-fn interval_timer_callback(timer: &Weak<Timer>) {
+fn interval_timer_callback(timer: &Weak<Timer>, expired_time: Duration) {
     let Some(timer) = timer.upgrade() else {
         return;
     };
 
     (timer.registered_callback)();
-    let interval = timer.interval.disable_irq().lock();
-    if *interval != Duration::ZERO {
-        timer.set_timeout(Timeout::After(*interval));
+    let interval = timer.interval();
+    if interval != Duration::ZERO {
+        // The timer is set again based on the expired time rather than the current time so that
+        // the timer does not drift. The missed expirations are skipped.
+        let nr_intervals = timer.missed_expirations_since(expired_time) as u128 + 1;
+        let next_expired_time = expired_time.as_nanos() + interval.as_nanos() * nr_intervals;
+        timer.set_timeout(Timeout::When(Duration::from_nanos(
+            next_expired_time as u64,
+        )));
     }
 }
 
@@ -5,7 +5,7 @@
 pub use core::{timer, Clock};
 
 use ::core::time::Duration;
-pub use system_time::{SystemTime, START_TIME};
+pub use system_time::{read_rtc_time, SystemTime, START_TIME};
 pub use timer::{Timer, TimerManager};
 
 use crate::prelude::*;
@@ -14,6 +14,7 @@ pub mod clocks;
 mod core;
 mod softirq;
 mod system_time;
+pub mod timekeeping;
 pub mod wait;
 
 pub type clockid_t = i32;
@@ -21,6 +22,9 @@ pub type time_t = i64;
 pub type suseconds_t = i64;
 pub type clock_t = i64;
 
+/// The frequency of the clock ticks that are reported to the user space (e.g., by `times`).
+pub const USER_HZ: u64 = 100;
+
 const NSEC_PER_USEC: i64 = 1_000;
 const USEC_PER_SEC: i64 = 1_000_000;
 const NSEC_PER_SEC: i64 = 1_000_000_000;
@@ -31,6 +35,11 @@ pub(super) fn init() {
     softirq::init();
 }
 
+/// Converts a duration to the number of the clock ticks that are reported to the user space.
+pub fn duration_to_clock_t(duration: Duration) -> clock_t {
+    (duration.as_nanos() * USER_HZ as u128 / NSEC_PER_SEC as u128) as clock_t
+}
+
 #[repr(C)]
 #[derive(Debug, Default, Copy, Clone, Pod)]
 pub struct timespec_t {
@@ -125,6 +134,58 @@ impl TryFrom<timeval_t> for Duration {
     }
 }
 
+/// This struct is corresponding to the `compat_timespec` struct in Linux, i.e., the
+/// `timespec` struct of 32-bit programs.
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, Pod)]
+pub struct compat_timespec_t {
+    pub sec: i32,
+    pub nsec: i32,
+}
+
+impl From<Duration> for compat_timespec_t {
+    fn from(duration: Duration) -> compat_timespec_t {
+        // The seconds are truncated after 2038, as Linux does.
+        let sec = duration.as_secs() as i32;
+        let nsec = duration.subsec_nanos() as i32;
+        compat_timespec_t { sec, nsec }
+    }
+}
+
+impl From<compat_timespec_t> for timespec_t {
+    fn from(value: compat_timespec_t) -> timespec_t {
+        timespec_t {
+            sec: value.sec as time_t,
+            nsec: value.nsec as i64,
+        }
+    }
+}
+
+impl TryFrom<compat_timespec_t> for Duration {
+    type Error = crate::Error;
+
+    fn try_from(value: compat_timespec_t) -> Result<Self> {
+        Duration::try_from(timespec_t::from(value))
+    }
+}
+
+/// This struct is corresponding to the `old_timeval32` struct in Linux, i.e., the `timeval`
+/// struct of 32-bit programs.
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, Pod)]
+pub struct compat_timeval_t {
+    pub sec: i32,
+    pub usec: i32,
+}
+
+impl From<Duration> for compat_timeval_t {
+    fn from(duration: Duration) -> compat_timeval_t {
+        let sec = duration.as_secs() as i32;
+        let usec = duration.subsec_micros() as i32;
+        compat_timeval_t { sec, usec }
+    }
+}
+
 /// The various flags for setting POSIX.1b interval timers:
 pub const TIMER_ABSTIME: i32 = 0x01;
 
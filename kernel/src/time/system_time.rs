@@ -2,10 +2,11 @@
 
 use core::time::Duration;
 
-use aster_time::{read_monotonic_time, read_start_time};
+use aster_time::read_start_time;
 use spin::Once;
 use time::{Date, Month, PrimitiveDateTime, Time};
 
+use super::timekeeping;
 use crate::prelude::*;
 
 /// This struct corresponds to `SystemTime` in Rust std.
@@ -41,10 +42,8 @@ impl SystemTime {
     /// Returns the current system time
     pub fn now() -> Self {
         // The get real time result should always be valid
-        START_TIME
-            .get()
-            .unwrap()
-            .checked_add(read_monotonic_time())
+        SystemTime::UNIX_EPOCH
+            .checked_add(timekeeping::real_time())
             .unwrap()
     }
 
@@ -81,6 +80,15 @@ impl SystemTime {
     }
 }
 
+/// Reads the RTC, and returns the duration since the unix epoch.
+///
+/// Unlike [`SystemTime::now`], the RTC keeps counting when the system is suspended.
+pub fn read_rtc_time() -> Duration {
+    convert_system_time(aster_time::read())
+        .and_then(|time| time.duration_since(&SystemTime::UNIX_EPOCH))
+        .unwrap_or_default()
+}
+
 /// convert ostd::time::Time to System time
 fn convert_system_time(system_time: aster_time::SystemTime) -> Result<SystemTime> {
     let month = match Month::try_from(system_time.month) {
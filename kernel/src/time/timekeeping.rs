@@ -0,0 +1,382 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The timekeeping core, which disciplines the system clocks.
+//!
+//! The raw monotonic time is read from the clocksource and is never adjusted. The other clocks
+//! are derived from it:
+//!  - The monotonic time runs at the raw rate corrected by the frequency adjustment, and slews
+//!    the pending offset at a bounded rate. It never goes backward.
+//!  - The boot time is the monotonic time plus the time spent in suspension.
+//!  - The real time is the monotonic time plus the wall clock offset, which can be stepped.
+//!  - The TAI time is the real time plus the TAI offset.
+//!
+//! The adjustments are made by NTP clients through [`adjtimex`].
+//!
+//! Reference: <https://man7.org/linux/man-pages/man2/adjtimex.2.html>
+
+use core::time::Duration;
+
+use aster_time::read_monotonic_time;
+use ostd::sync::SpinLock;
+
+use super::{
+    system_time::START_TIME_AS_DURATION, timeval_t, NSEC_PER_SEC, NSEC_PER_USEC, USEC_PER_SEC,
+    USER_HZ,
+};
+use crate::prelude::*;
+
+/// This struct is corresponding to the `timex` struct in Linux.
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, Pod)]
+pub struct timex_t {
+    pub modes: u32,
+    _pad0: u32,
+    pub offset: i64,
+    pub freq: i64,
+    pub maxerror: i64,
+    pub esterror: i64,
+    pub status: i32,
+    _pad1: u32,
+    pub constant: i64,
+    pub precision: i64,
+    pub tolerance: i64,
+    pub time: timeval_t,
+    pub tick: i64,
+    pub ppsfreq: i64,
+    pub jitter: i64,
+    pub shift: i32,
+    _pad2: u32,
+    pub stabil: i64,
+    pub jitcnt: i64,
+    pub calcnt: i64,
+    pub errcnt: i64,
+    pub stbcnt: i64,
+    pub tai: i32,
+    _reserved: [i32; 11],
+}
+
+const ADJ_OFFSET: u32 = 0x0001;
+const ADJ_FREQUENCY: u32 = 0x0002;
+const ADJ_MAXERROR: u32 = 0x0004;
+const ADJ_ESTERROR: u32 = 0x0008;
+const ADJ_STATUS: u32 = 0x0010;
+const ADJ_TIMECONST: u32 = 0x0020;
+const ADJ_TAI: u32 = 0x0080;
+const ADJ_SETOFFSET: u32 = 0x0100;
+const ADJ_MICRO: u32 = 0x1000;
+const ADJ_NANO: u32 = 0x2000;
+const ADJ_TICK: u32 = 0x4000;
+const ADJ_OFFSET_SINGLESHOT: u32 = 0x8001;
+/// Reads the remaining offset of `adjtime` without changing anything.
+pub const ADJ_OFFSET_SS_READ: u32 = 0xa001;
+
+const STA_UNSYNC: i32 = 0x0040;
+const STA_NANO: i32 = 0x2000;
+/// The status bits that cannot be changed by the user space.
+const STA_RONLY: i32 = 0xff00u32 as i32;
+
+const TIME_OK: i32 = 0;
+const TIME_ERROR: i32 = 5;
+
+/// The maximum frequency adjustment, in parts per million (ppm).
+const MAX_FREQ_PPM: i64 = 500;
+/// The rate at which the offsets are slewed, in ppm.
+const SLEW_RATE_PPM: i64 = 500;
+/// The maximum offset that can be slewed in the PLL mode, in nanoseconds.
+const MAX_PHASE_NS: i64 = 500_000_000;
+/// The maximum error of the clock, in microseconds.
+const MAX_ERROR_USEC: i64 = 16_000_000;
+/// The frequencies in the `timex` struct are in ppm with a 16-bit fraction.
+const FREQ_SHIFT: u32 = 16;
+const PPM: i128 = 1_000_000;
+
+/// The nominal length of a tick, in microseconds.
+const NOMINAL_TICK_USEC: i64 = USEC_PER_SEC / USER_HZ as i64;
+
+struct Timekeeper {
+    /// The raw monotonic time of the last checkpoint, in nanoseconds.
+    base_raw: u64,
+    /// The monotonic time of the last checkpoint, in nanoseconds.
+    base_mono: u64,
+    /// The frequency adjustment, in ppm with a 16-bit fraction.
+    freq: i64,
+    /// The length of a tick, in microseconds, which also adjusts the frequency.
+    tick_usec: i64,
+    /// The offset that remains to be slewed since the last checkpoint, in nanoseconds.
+    offset: i64,
+    /// The real time minus the monotonic time since the unix epoch, in nanoseconds.
+    wall_offset: i64,
+    /// The time spent in suspension, in nanoseconds.
+    sleep_time: u64,
+    /// The TAI time minus the real time, in seconds.
+    tai: i32,
+    status: i32,
+    maxerror: i64,
+    esterror: i64,
+    constant: i64,
+}
+
+static TIMEKEEPER: SpinLock<Timekeeper> = SpinLock::new(Timekeeper {
+    base_raw: 0,
+    base_mono: 0,
+    freq: 0,
+    tick_usec: NOMINAL_TICK_USEC,
+    offset: 0,
+    wall_offset: 0,
+    sleep_time: 0,
+    tai: 0,
+    status: STA_UNSYNC,
+    maxerror: MAX_ERROR_USEC,
+    esterror: MAX_ERROR_USEC,
+    constant: 2,
+});
+
+impl Timekeeper {
+    /// Returns the frequency adjustment and the slewed offset for the raw time since the last
+    /// checkpoint, both in nanoseconds.
+    fn adjustments(&self, elapsed: u64) -> (i128, i128) {
+        let tick_ppm = (self.tick_usec * USER_HZ as i64 - USEC_PER_SEC) as i128;
+        let scaled_ppm = self.freq as i128 + (tick_ppm << FREQ_SHIFT);
+        let freq_adjustment = elapsed as i128 * scaled_ppm / (PPM << FREQ_SHIFT);
+
+        let max_slew = elapsed as i128 * SLEW_RATE_PPM as i128 / PPM;
+        let slew = (self.offset as i128).clamp(-max_slew, max_slew);
+
+        (freq_adjustment, slew)
+    }
+
+    fn monotonic_at(&self, raw: u64) -> u64 {
+        let elapsed = raw.saturating_sub(self.base_raw);
+        let (freq_adjustment, slew) = self.adjustments(elapsed);
+        (self.base_mono as i128 + elapsed as i128 + freq_adjustment + slew) as u64
+    }
+
+    /// Records the current time as the base, so that the adjustments take effect from now on.
+    fn checkpoint(&mut self, raw: u64) {
+        let elapsed = raw.saturating_sub(self.base_raw);
+        let (_, slew) = self.adjustments(elapsed);
+        self.base_mono = self.monotonic_at(raw);
+        self.base_raw = raw;
+        self.offset -= slew as i64;
+    }
+
+    fn real_at(&self, raw: u64) -> i128 {
+        let start = START_TIME_AS_DURATION.get().unwrap().as_nanos() as i128;
+        start + self.monotonic_at(raw) as i128 + self.wall_offset as i128
+    }
+}
+
+fn read_raw_nanos() -> u64 {
+    read_monotonic_time().as_nanos() as u64
+}
+
+fn nanos_to_duration(nanos: i128) -> Duration {
+    Duration::from_nanos(nanos.max(0) as u64)
+}
+
+/// Returns the raw monotonic time, which is not affected by any adjustments.
+pub fn monotonic_raw_time() -> Duration {
+    read_monotonic_time()
+}
+
+/// Returns the monotonic time.
+pub fn monotonic_time() -> Duration {
+    let raw = read_raw_nanos();
+    Duration::from_nanos(TIMEKEEPER.disable_irq().lock().monotonic_at(raw))
+}
+
+/// Returns the boot time, which includes the time spent in suspension.
+pub fn boot_time() -> Duration {
+    let raw = read_raw_nanos();
+    let timekeeper = TIMEKEEPER.disable_irq().lock();
+    Duration::from_nanos(timekeeper.monotonic_at(raw) + timekeeper.sleep_time)
+}
+
+/// Returns the real time since the unix epoch.
+pub fn real_time() -> Duration {
+    let raw = read_raw_nanos();
+    nanos_to_duration(TIMEKEEPER.disable_irq().lock().real_at(raw))
+}
+
+/// Returns the TAI time since the unix epoch.
+pub fn tai_time() -> Duration {
+    let raw = read_raw_nanos();
+    let timekeeper = TIMEKEEPER.disable_irq().lock();
+    let tai = timekeeper.tai as i128 * NSEC_PER_SEC as i128;
+    nanos_to_duration(timekeeper.real_at(raw) + tai)
+}
+
+/// The adjusted clocks at a raw monotonic time.
+#[derive(Debug, Clone, Copy)]
+pub struct AdjustedTime {
+    pub monotonic: Duration,
+    pub boot: Duration,
+    pub real: Duration,
+}
+
+/// Returns the adjusted clocks at the raw monotonic time.
+///
+/// This is used to publish the adjusted clocks with the raw readings of the clocksource.
+pub fn adjusted_at(raw: Duration) -> AdjustedTime {
+    let raw = raw.as_nanos() as u64;
+    let timekeeper = TIMEKEEPER.disable_irq().lock();
+    let monotonic = timekeeper.monotonic_at(raw);
+    AdjustedTime {
+        monotonic: Duration::from_nanos(monotonic),
+        boot: Duration::from_nanos(monotonic + timekeeper.sleep_time),
+        real: nanos_to_duration(timekeeper.real_at(raw)),
+    }
+}
+
+/// Accounts the time spent in suspension, during which the clocksource did not advance.
+///
+/// This makes the boot time and the real time skip over the suspension, while the monotonic
+/// time does not.
+pub fn inject_sleep_time(duration: Duration) {
+    let nanos = duration.as_nanos() as u64;
+    let mut timekeeper = TIMEKEEPER.disable_irq().lock();
+    timekeeper.sleep_time += nanos;
+    timekeeper.wall_offset += nanos as i64;
+}
+
+/// Adjusts the clocks as specified by `timex`, and fills `timex` with the current state.
+///
+/// It returns the state of the clock (e.g., `TIME_OK`), as the return value of `adjtimex`.
+/// Leap seconds are not inserted or deleted, so the state is never `TIME_INS` or `TIME_DEL`.
+pub fn adjtimex(timex: &mut timex_t) -> Result<i32> {
+    let modes = timex.modes;
+
+    // `adjtime` cannot be mixed with the other modes.
+    if modes & ADJ_OFFSET_SINGLESHOT == ADJ_OFFSET_SINGLESHOT
+        && modes != ADJ_OFFSET_SINGLESHOT
+        && modes != ADJ_OFFSET_SS_READ
+    {
+        return_errno_with_message!(Errno::EINVAL, "adjtime cannot be mixed with other modes");
+    }
+    if modes & ADJ_TICK != 0
+        && !(NOMINAL_TICK_USEC * 9 / 10..=NOMINAL_TICK_USEC * 11 / 10).contains(&timex.tick)
+    {
+        return_errno_with_message!(Errno::EINVAL, "the tick is out of range");
+    }
+    let step = if modes & ADJ_SETOFFSET != 0 {
+        Some(setoffset_to_nanos(timex, modes & ADJ_NANO != 0)?)
+    } else {
+        None
+    };
+
+    let raw = read_raw_nanos();
+    let mut timekeeper = TIMEKEEPER.disable_irq().lock();
+    timekeeper.checkpoint(raw);
+
+    if let Some(step) = step {
+        if timekeeper.real_at(raw) + (step as i128) < 0 {
+            return_errno_with_message!(Errno::EINVAL, "the time cannot be before the epoch");
+        }
+        timekeeper.wall_offset += step;
+    }
+
+    let old_offset = timekeeper.offset;
+    if modes == ADJ_OFFSET_SINGLESHOT {
+        timekeeper.offset = timex.offset.saturating_mul(NSEC_PER_USEC);
+    } else if modes != ADJ_OFFSET_SS_READ {
+        if modes & ADJ_STATUS != 0 {
+            timekeeper.status = (timekeeper.status & STA_RONLY) | (timex.status & !STA_RONLY);
+        }
+        if modes & ADJ_NANO != 0 {
+            timekeeper.status |= STA_NANO;
+        }
+        if modes & ADJ_MICRO != 0 {
+            timekeeper.status &= !STA_NANO;
+        }
+        if modes & ADJ_FREQUENCY != 0 {
+            let max_freq = MAX_FREQ_PPM << FREQ_SHIFT;
+            timekeeper.freq = timex.freq.clamp(-max_freq, max_freq);
+        }
+        if modes & ADJ_MAXERROR != 0 {
+            timekeeper.maxerror = timex.maxerror.clamp(0, MAX_ERROR_USEC);
+        }
+        if modes & ADJ_ESTERROR != 0 {
+            timekeeper.esterror = timex.esterror.clamp(0, MAX_ERROR_USEC);
+        }
+        if modes & ADJ_TIMECONST != 0 {
+            timekeeper.constant = timex.constant.clamp(0, 10);
+        }
+        if modes & ADJ_TAI != 0 && timex.constant >= 0 {
+            // The TAI offset is passed in the `constant` field, as Linux does.
+            timekeeper.tai = timex.constant as i32;
+        }
+        if modes & ADJ_TICK != 0 {
+            timekeeper.tick_usec = timex.tick;
+        }
+        if modes & ADJ_OFFSET != 0 {
+            let offset = if timekeeper.status & STA_NANO != 0 {
+                timex.offset
+            } else {
+                timex.offset.saturating_mul(NSEC_PER_USEC)
+            };
+            timekeeper.offset = offset.clamp(-MAX_PHASE_NS, MAX_PHASE_NS);
+        }
+    }
+
+    let is_nano = timekeeper.status & STA_NANO != 0;
+    timex.offset = if modes & ADJ_OFFSET_SINGLESHOT == ADJ_OFFSET_SINGLESHOT {
+        // `adjtime` reports the offset that remained before the call, in microseconds.
+        old_offset / NSEC_PER_USEC
+    } else if is_nano {
+        timekeeper.offset
+    } else {
+        timekeeper.offset / NSEC_PER_USEC
+    };
+    timex.freq = timekeeper.freq;
+    timex.maxerror = timekeeper.maxerror;
+    timex.esterror = timekeeper.esterror;
+    timex.status = timekeeper.status;
+    timex.constant = timekeeper.constant;
+    timex.precision = 1;
+    timex.tolerance = MAX_FREQ_PPM << FREQ_SHIFT;
+    timex.tick = timekeeper.tick_usec;
+    timex.tai = timekeeper.tai;
+    // PPS signals are not supported.
+    timex.ppsfreq = 0;
+    timex.jitter = 0;
+    timex.shift = 0;
+    timex.stabil = 0;
+    timex.jitcnt = 0;
+    timex.calcnt = 0;
+    timex.errcnt = 0;
+    timex.stbcnt = 0;
+
+    let now = nanos_to_duration(timekeeper.real_at(raw));
+    timex.time = timeval_t {
+        sec: now.as_secs() as i64,
+        usec: if is_nano {
+            now.subsec_nanos() as i64
+        } else {
+            now.subsec_micros() as i64
+        },
+    };
+
+    if timekeeper.status & STA_UNSYNC != 0 {
+        Ok(TIME_ERROR)
+    } else {
+        Ok(TIME_OK)
+    }
+}
+
+/// Returns the step passed with `ADJ_SETOFFSET`, in nanoseconds.
+fn setoffset_to_nanos(timex: &timex_t, is_nano: bool) -> Result<i64> {
+    let (subsec_limit, nanos_per_unit) = if is_nano {
+        (NSEC_PER_SEC, 1)
+    } else {
+        (USEC_PER_SEC, NSEC_PER_USEC)
+    };
+    if !(0..subsec_limit).contains(&timex.time.usec) {
+        return_errno_with_message!(Errno::EINVAL, "the offset is not normalized");
+    }
+    timex
+        .time
+        .sec
+        .checked_mul(NSEC_PER_SEC)
+        .and_then(|nanos| nanos.checked_add(timex.time.usec * nanos_per_unit))
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "the offset is too large"))
+}
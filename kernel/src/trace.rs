@@ -0,0 +1,133 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The tracepoints of the kernel.
+//!
+//! The tracepoints are registered to OSTD (see [`ostd::trace`]), so that they can be enabled
+//! and read via the tracefs, along with the tracepoints in OSTD.
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use ostd::{
+    cpu::{CpuId, PinCurrentCpu},
+    cpu_local,
+    task::disable_preempt,
+    trace::{register_tracepoint, Tracepoint},
+};
+
+use crate::{prelude::*, process::posix_thread::AsPosixThread, thread::Thread, vm::perms::VmPerms};
+
+/// The tracepoint hit when a CPU switches to another thread.
+///
+/// The kernel threads without TIDs are reported as TID 0.
+pub static SCHED_SWITCH: Tracepoint = Tracepoint::new("sched", "sched_switch", |args, f| {
+    write!(f, "prev_pid={} next_pid={}", args[0], args[1])
+});
+
+/// The tracepoint hit when a thread is woken and put into the run queue of a CPU.
+pub static SCHED_WAKEUP: Tracepoint = Tracepoint::new("sched", "sched_wakeup", |args, f| {
+    write!(f, "pid={} target_cpu={}", args[0], args[1])
+});
+
+/// The tracepoint hit when a thread is moved to another CPU.
+pub static SCHED_MIGRATE_TASK: Tracepoint =
+    Tracepoint::new("sched", "sched_migrate_task", |args, f| {
+        write!(
+            f,
+            "pid={} orig_cpu={} dest_cpu={}",
+            args[0], args[1], args[2]
+        )
+    });
+
+/// The tracepoint hit when a woken thread starts running, with the latency of the wakeup.
+///
+/// The latency is also reported as the bucket of a power-of-two histogram (in microseconds), so
+/// that the histogram can be built by counting the events of each bucket.
+pub static SCHED_WAKEUP_LATENCY: Tracepoint =
+    Tracepoint::new("sched", "sched_wakeup_latency", |args, f| {
+        write!(
+            f,
+            "pid={} cpu={} latency={}ns bucket=<{}us",
+            args[0],
+            args[1],
+            args[2],
+            latency_bucket_us(args[2])
+        )
+    });
+
+/// The tracepoint hit when a system call starts.
+pub static SYS_ENTER: Tracepoint = Tracepoint::new("raw_syscalls", "sys_enter", |args, f| {
+    write!(
+        f,
+        "NR {} ({:x}, {:x}, {:x}, {:x}, {:x}, {:x})",
+        args[0], args[1], args[2], args[3], args[4], args[5], args[6]
+    )
+});
+
+/// The tracepoint hit when a system call returns.
+pub static SYS_EXIT: Tracepoint = Tracepoint::new("raw_syscalls", "sys_exit", |args, f| {
+    write!(f, "NR {} = {}", args[0], args[1] as i64)
+});
+
+/// The tracepoint hit when a page fault of the user space is handled.
+pub static PAGE_FAULT_USER: Tracepoint =
+    Tracepoint::new("exceptions", "page_fault_user", |args, f| {
+        let perms = VmPerms::from_bits_truncate(args[1] as u32);
+        write!(f, "address={:#x} perms={:?}", args[0], perms)
+    });
+
+cpu_local! {
+    /// The TID of the thread that was switched to on the CPU.
+    static LAST_TID: AtomicU32 = AtomicU32::new(0);
+}
+
+pub(super) fn init() {
+    register_tracepoint(&SCHED_SWITCH);
+    register_tracepoint(&SCHED_WAKEUP);
+    register_tracepoint(&SCHED_MIGRATE_TASK);
+    register_tracepoint(&SCHED_WAKEUP_LATENCY);
+    register_tracepoint(&SYS_ENTER);
+    register_tracepoint(&SYS_EXIT);
+    register_tracepoint(&PAGE_FAULT_USER);
+}
+
+/// Records a [`SCHED_SWITCH`] event after the current CPU switches to the thread.
+pub(crate) fn trace_sched_switch(next_thread: Option<&Arc<Thread>>) {
+    let next_tid = next_thread.map_or(0, |thread| tid_of(thread));
+
+    let preempt_guard = disable_preempt();
+    let prev_tid = LAST_TID
+        .get_on_cpu(preempt_guard.current_cpu())
+        .swap(next_tid, Ordering::Relaxed);
+    SCHED_SWITCH.emit(&[prev_tid as u64, next_tid as u64]);
+}
+
+/// Records a [`SCHED_WAKEUP`] event.
+pub(crate) fn trace_sched_wakeup(thread: &Thread, target_cpu: CpuId) {
+    SCHED_WAKEUP.emit(&[tid_of(thread) as u64, target_cpu.as_usize() as u64]);
+}
+
+/// Records a [`SCHED_MIGRATE_TASK`] event.
+pub(crate) fn trace_sched_migrate_task(thread: &Thread, orig_cpu: CpuId, dest_cpu: CpuId) {
+    SCHED_MIGRATE_TASK.emit(&[
+        tid_of(thread) as u64,
+        orig_cpu.as_usize() as u64,
+        dest_cpu.as_usize() as u64,
+    ]);
+}
+
+/// Records a [`SCHED_WAKEUP_LATENCY`] event.
+pub(crate) fn trace_sched_wakeup_latency(thread: &Thread, cpu: CpuId, latency_ns: u64) {
+    SCHED_WAKEUP_LATENCY.emit(&[tid_of(thread) as u64, cpu.as_usize() as u64, latency_ns]);
+}
+
+/// Returns the TID of the thread, or 0 for the kernel threads without TIDs.
+fn tid_of(thread: &Thread) -> u32 {
+    thread
+        .as_posix_thread()
+        .map_or(0, |posix_thread| posix_thread.tid())
+}
+
+/// Returns the upper bound of the power-of-two bucket that the latency falls in.
+fn latency_bucket_us(latency_ns: u64) -> u64 {
+    (latency_ns / 1000 + 1).next_power_of_two()
+}
@@ -1,5 +1,7 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use core::ops::Range;
+
 use ostd::mm::{Infallible, VmSpace};
 
 use crate::prelude::*;
@@ -38,6 +40,29 @@ impl TryFrom<UserIoVec> for IoVec {
     }
 }
 
+/// A user space IO vector of 32-bit programs, i.e., `struct compat_iovec`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct CompatUserIoVec {
+    base: u32,
+    len: i32,
+}
+
+impl TryFrom<CompatUserIoVec> for IoVec {
+    type Error = Error;
+
+    fn try_from(value: CompatUserIoVec) -> Result<Self> {
+        if value.len < 0 {
+            return_errno_with_message!(Errno::EINVAL, "the length of IO vector cannot be negative");
+        }
+
+        Ok(IoVec {
+            base: value.base as Vaddr,
+            len: value.len as usize,
+        })
+    }
+}
+
 impl IoVec {
     /// Returns whether the `IoVec` points to an empty user buffer.
     const fn is_empty(&self) -> bool {
@@ -58,13 +83,20 @@ fn copy_iovs_and_convert<'a, T: 'a>(
     user_space: &'a CurrentUserSpace<'a>,
     start_addr: Vaddr,
     count: usize,
+    is_compat: bool,
     convert_iovec: impl Fn(&IoVec, &'a VmSpace) -> Result<T>,
 ) -> Result<Box<[T]>> {
     let vm_space = user_space.root_vmar().vm_space();
 
     let mut v = Vec::with_capacity(count);
     for idx in 0..count {
-        let iov = {
+        let iov = if is_compat {
+            let addr = start_addr + idx * core::mem::size_of::<CompatUserIoVec>();
+            let uiov: CompatUserIoVec = vm_space
+                .reader(addr, core::mem::size_of::<CompatUserIoVec>())?
+                .read_val()?;
+            IoVec::try_from(uiov)?
+        } else {
             let addr = start_addr + idx * core::mem::size_of::<UserIoVec>();
             let uiov: UserIoVec = vm_space
                 .reader(addr, core::mem::size_of::<UserIoVec>())?
@@ -83,6 +115,24 @@ fn copy_iovs_and_convert<'a, T: 'a>(
     Ok(v.into_boxed_slice())
 }
 
+/// Reads the user-provided IO vectors as address ranges, which is used if the buffers are not
+/// in the current address space (e.g., the remote buffers of `process_vm_readv`).
+///
+/// The empty buffers are skipped.
+pub fn read_user_io_vec_ranges(
+    user_space: &CurrentUserSpace,
+    start_addr: Vaddr,
+    count: usize,
+) -> Result<Vec<Range<Vaddr>>> {
+    let ranges = copy_iovs_and_convert(user_space, start_addr, count, false, |iov, _| {
+        let end = iov.base.checked_add(iov.len).ok_or_else(|| {
+            Error::with_message(Errno::EFAULT, "the IO vector is out of the address space")
+        })?;
+        Ok(iov.base..end)
+    })?;
+    Ok(ranges.into_vec())
+}
+
 /// A collection of [`VmReader`]s.
 ///
 /// Such readers are built from user-provided buffer, so it's always fallible.
@@ -100,7 +150,17 @@ impl<'a> VmReaderArray<'a> {
         start_addr: Vaddr,
         count: usize,
     ) -> Result<Self> {
-        let readers = copy_iovs_and_convert(user_space, start_addr, count, IoVec::reader)?;
+        let readers = copy_iovs_and_convert(user_space, start_addr, count, false, IoVec::reader)?;
+        Ok(Self(readers))
+    }
+
+    /// Creates a new `IoVecReader` from user-provided io vec buffer of a 32-bit program.
+    pub fn from_compat_user_io_vecs(
+        user_space: &'a CurrentUserSpace<'a>,
+        start_addr: Vaddr,
+        count: usize,
+    ) -> Result<Self> {
+        let readers = copy_iovs_and_convert(user_space, start_addr, count, true, IoVec::reader)?;
         Ok(Self(readers))
     }
 
@@ -117,7 +177,17 @@ impl<'a> VmWriterArray<'a> {
         start_addr: Vaddr,
         count: usize,
     ) -> Result<Self> {
-        let writers = copy_iovs_and_convert(user_space, start_addr, count, IoVec::writer)?;
+        let writers = copy_iovs_and_convert(user_space, start_addr, count, false, IoVec::writer)?;
+        Ok(Self(writers))
+    }
+
+    /// Creates a new `IoVecWriter` from user-provided io vec buffer of a 32-bit program.
+    pub fn from_compat_user_io_vecs(
+        user_space: &'a CurrentUserSpace<'a>,
+        start_addr: Vaddr,
+        count: usize,
+    ) -> Result<Self> {
+        let writers = copy_iovs_and_convert(user_space, start_addr, count, true, IoVec::writer)?;
         Ok(Self(writers))
     }
 
@@ -1,6 +1,6 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use aster_bigtcp::wire::{Ipv4Address, PortNum};
+use aster_bigtcp::wire::{Ipv4Address, Ipv6Address, PortNum};
 
 use super::family::CSocketAddrFamily;
 use crate::prelude::*;
@@ -14,7 +14,7 @@ use crate::prelude::*;
 /// <https://elixir.bootlin.com/linux/v6.10.2/source/include/uapi/linux/in.h#L256>.
 #[repr(C)]
 #[derive(Debug, Clone, Copy, Pod)]
-pub(super) struct CSocketAddrInet {
+pub(crate) struct CSocketAddrInet {
     /// Address family (AF_INET).
     sin_family: u16,
     /// Port number.
@@ -42,6 +42,43 @@ impl From<CSocketAddrInet> for (Ipv4Address, PortNum) {
     }
 }
 
+/// IPv6 socket address.
+///
+/// See <https://www.man7.org/linux/man-pages/man7/ipv6.7.html>.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(crate) struct CSocketAddrInet6 {
+    /// Address family (AF_INET6).
+    sin6_family: u16,
+    /// Port number.
+    sin6_port: CPortNum,
+    /// IPv6 flow information.
+    sin6_flowinfo: u32,
+    /// IPv6 address.
+    sin6_addr: CInet6Addr,
+    /// Scope ID.
+    sin6_scope_id: u32,
+}
+
+impl From<(Ipv6Address, PortNum)> for CSocketAddrInet6 {
+    fn from(value: (Ipv6Address, PortNum)) -> Self {
+        Self {
+            sin6_family: CSocketAddrFamily::AF_INET6 as u16,
+            sin6_port: value.1.into(),
+            sin6_flowinfo: 0,
+            sin6_addr: value.0.into(),
+            // TODO: Support scope IDs for link-local addresses.
+            sin6_scope_id: 0,
+        }
+    }
+}
+
+impl From<CSocketAddrInet6> for (Ipv6Address, PortNum) {
+    fn from(value: CSocketAddrInet6) -> Self {
+        (value.sin6_addr.into(), value.sin6_port.into())
+    }
+}
+
 /// IPv4 4-byte address.
 #[repr(C)]
 #[derive(Debug, Clone, Copy, Pod)]
@@ -63,6 +100,27 @@ impl From<CInetAddr> for Ipv4Address {
     }
 }
 
+/// IPv6 16-byte address.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct CInet6Addr {
+    s6_addr: [u8; 16],
+}
+
+impl From<Ipv6Address> for CInet6Addr {
+    fn from(value: Ipv6Address) -> Self {
+        Self {
+            s6_addr: value.octets(),
+        }
+    }
+}
+
+impl From<CInet6Addr> for Ipv6Address {
+    fn from(value: CInet6Addr) -> Self {
+        Self::from(value.s6_addr)
+    }
+}
+
 /// TCP/UDP port number.
 #[repr(C)]
 #[derive(Debug, Clone, Copy, Pod)]
@@ -4,8 +4,12 @@ pub use family::{
     read_socket_addr_from_user, write_socket_addr_to_user, write_socket_addr_with_max_len,
     CSocketAddrFamily,
 };
+pub(crate) use ip::{CSocketAddrInet, CSocketAddrInet6};
+pub(crate) use packet::CSocketAddrLl;
 
 mod family;
 mod ip;
+mod netlink;
+mod packet;
 mod unix;
 mod vsock;
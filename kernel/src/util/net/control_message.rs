@@ -0,0 +1,227 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::CSocketOptionLevel;
+use crate::{
+    fs::file_table::{FdFlags, FileDesc},
+    net::socket::{unix::UnixCredentials, ControlMessage},
+    prelude::*,
+    time::{timespec_t, timeval_t},
+};
+
+/// The header of a control message (i.e., `struct cmsghdr` in Linux).
+///
+/// The definition is from https://elixir.bootlin.com/linux/v6.0.9/source/include/linux/socket.h#L105.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct CControlHeader {
+    /// The length of the control message, including the header
+    cmsg_len: usize,
+    /// The originating protocol
+    cmsg_level: i32,
+    /// The protocol-specific type
+    cmsg_type: i32,
+}
+
+const CONTROL_HEADER_LEN: usize = size_of::<CControlHeader>();
+
+/// Control message types at the `SOL_SOCKET` level.
+///
+/// The definition is from https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/asm-generic/socket.h.
+const SCM_RIGHTS: i32 = 1;
+const SCM_CREDENTIALS: i32 = 2;
+const SCM_TIMESTAMP: i32 = 29;
+const SCM_TIMESTAMPNS: i32 = 35;
+
+/// The maximum number of file descriptors that can be passed in one message.
+///
+/// Reference: <https://elixir.bootlin.com/linux/v6.0.9/source/include/net/scm.h#L24>.
+const SCM_MAX_FD: usize = 253;
+
+/// Aligns the length of a control message (i.e., `CMSG_ALIGN` in Linux).
+const fn align_control_len(len: usize) -> usize {
+    len.next_multiple_of(size_of::<usize>())
+}
+
+impl ControlMessage {
+    /// Returns the length of the payload of the C counterpart.
+    fn c_payload_len(&self) -> usize {
+        match self {
+            ControlMessage::Timestamp(_) => size_of::<timeval_t>(),
+            ControlMessage::TimestampNs(_) => size_of::<timespec_t>(),
+            ControlMessage::Rights(files) => files.len() * size_of::<FileDesc>(),
+            ControlMessage::Credentials(_) => size_of::<UnixCredentials>(),
+        }
+    }
+
+    /// Returns the level, the type, and the payload of the C counterpart.
+    ///
+    /// The files passed via `SCM_RIGHTS` are installed in the file table of the current thread.
+    /// Only the files whose file descriptors fit in `max_payload_len` bytes are installed, and
+    /// the others are discarded.
+    fn to_c_parts(
+        &self,
+        max_payload_len: usize,
+        fd_flags: FdFlags,
+        ctx: &Context,
+    ) -> (CSocketOptionLevel, i32, Vec<u8>) {
+        match self {
+            ControlMessage::Timestamp(time) => {
+                let timeval = timeval_t::from(*time);
+                (
+                    CSocketOptionLevel::SOL_SOCKET,
+                    SCM_TIMESTAMP,
+                    timeval.as_bytes().to_vec(),
+                )
+            }
+            ControlMessage::TimestampNs(time) => {
+                let timespec = timespec_t::from(*time);
+                (
+                    CSocketOptionLevel::SOL_SOCKET,
+                    SCM_TIMESTAMPNS,
+                    timespec.as_bytes().to_vec(),
+                )
+            }
+            ControlMessage::Rights(files) => {
+                let num_fds = files.len().min(max_payload_len / size_of::<FileDesc>());
+
+                let file_table = ctx.thread_local.file_table().borrow();
+                let mut file_table_locked = file_table.write();
+                // The files that cannot be installed due to the file limit are discarded.
+                let payload = files[..num_fds]
+                    .iter()
+                    .map_while(|file| file_table_locked.insert(file.clone(), fd_flags).ok())
+                    .flat_map(|fd| fd.to_ne_bytes())
+                    .collect();
+
+                (CSocketOptionLevel::SOL_SOCKET, SCM_RIGHTS, payload)
+            }
+            ControlMessage::Credentials(cred) => (
+                CSocketOptionLevel::SOL_SOCKET,
+                SCM_CREDENTIALS,
+                cred.as_bytes().to_vec(),
+            ),
+        }
+    }
+}
+
+/// Reads control messages from the user space.
+///
+/// Only the control messages at the `SOL_SOCKET` level can be sent now. The files passed via
+/// `SCM_RIGHTS` are looked up in the file table of the current thread.
+pub fn read_control_messages_from_user(
+    addr: Vaddr,
+    len: usize,
+    ctx: &Context,
+) -> Result<Vec<ControlMessage>> {
+    let user_space = ctx.user_space();
+
+    let mut messages = Vec::new();
+    let mut num_files = 0;
+
+    let mut read_len = 0;
+    while len - read_len >= CONTROL_HEADER_LEN {
+        let message_addr = addr + read_len;
+        let header = user_space.read_val::<CControlHeader>(message_addr)?;
+        if header.cmsg_len < CONTROL_HEADER_LEN || header.cmsg_len > len - read_len {
+            return_errno_with_message!(Errno::EINVAL, "the control message length is invalid");
+        }
+
+        let payload_addr = message_addr + CONTROL_HEADER_LEN;
+        let payload_len = header.cmsg_len - CONTROL_HEADER_LEN;
+
+        if header.cmsg_level != CSocketOptionLevel::SOL_SOCKET as i32 {
+            // TODO: Support sending protocol-level control messages
+            warn!(
+                "the control message at level {} is not supported",
+                header.cmsg_level
+            );
+        } else {
+            match header.cmsg_type {
+                SCM_RIGHTS => {
+                    let num_fds = payload_len / size_of::<FileDesc>();
+                    num_files += num_fds;
+                    if num_files > SCM_MAX_FD {
+                        return_errno_with_message!(Errno::EINVAL, "too many files are passed");
+                    }
+
+                    let file_table = ctx.thread_local.file_table().borrow();
+                    let file_table_locked = file_table.read();
+                    let files = (0..num_fds)
+                        .map(|i| {
+                            let fd = user_space
+                                .read_val::<FileDesc>(payload_addr + i * size_of::<FileDesc>())?;
+                            file_table_locked.get_file(fd).cloned()
+                        })
+                        .collect::<Result<Vec<_>>>()?;
+                    messages.push(ControlMessage::Rights(files));
+                }
+                SCM_CREDENTIALS => {
+                    if payload_len != size_of::<UnixCredentials>() {
+                        return_errno_with_message!(
+                            Errno::EINVAL,
+                            "the credentials have an invalid length"
+                        );
+                    }
+
+                    let cred = user_space.read_val::<UnixCredentials>(payload_addr)?;
+                    cred.check_current()?;
+                    messages.push(ControlMessage::Credentials(cred));
+                }
+                _ => return_errno_with_message!(
+                    Errno::EINVAL,
+                    "the control message type is not supported"
+                ),
+            }
+        }
+
+        read_len = (read_len + align_control_len(header.cmsg_len)).min(len);
+    }
+
+    Ok(messages)
+}
+
+/// Writes control messages to the user space.
+///
+/// The messages that do not fit in the buffer are truncated like Linux does. This method returns
+/// the number of bytes written and whether some messages were truncated (i.e., `MSG_CTRUNC`).
+pub fn write_control_messages_to_user(
+    messages: &[ControlMessage],
+    addr: Vaddr,
+    max_len: usize,
+    fd_flags: FdFlags,
+    ctx: &Context,
+) -> Result<(usize, bool)> {
+    let user_space = ctx.user_space();
+
+    let mut written_len = 0;
+    for message in messages {
+        let remain_len = max_len - written_len;
+        if remain_len < CONTROL_HEADER_LEN {
+            return Ok((written_len, true));
+        }
+
+        let (level, type_, payload) =
+            message.to_c_parts(remain_len - CONTROL_HEADER_LEN, fd_flags, ctx);
+        let message_len = (CONTROL_HEADER_LEN + payload.len()).min(remain_len);
+        let is_truncated = message_len < CONTROL_HEADER_LEN + message.c_payload_len();
+
+        let header = CControlHeader {
+            cmsg_len: message_len,
+            cmsg_level: level as i32,
+            cmsg_type: type_,
+        };
+        let message_addr = addr + written_len;
+        user_space.write_val(message_addr, &header)?;
+        user_space.write_bytes(
+            message_addr + CONTROL_HEADER_LEN,
+            &mut VmReader::from(&payload[..message_len - CONTROL_HEADER_LEN]),
+        )?;
+
+        written_len += align_control_len(message_len).min(remain_len);
+        if is_truncated {
+            return Ok((written_len, true));
+        }
+    }
+
+    Ok((written_len, false))
+}
@@ -1,6 +1,7 @@
 // SPDX-License-Identifier: MPL-2.0
 
 mod addr;
+mod control_message;
 mod options;
 mod socket;
 
@@ -8,5 +9,6 @@ pub use addr::{
     read_socket_addr_from_user, write_socket_addr_to_user, write_socket_addr_with_max_len,
     CSocketAddrFamily,
 };
+pub(crate) use addr::{CSocketAddrInet, CSocketAddrInet6, CSocketAddrLl};
 pub use options::{new_raw_socket_option, CSocketOptionLevel};
 pub use socket::{CUserMsgHdr, Protocol, SockFlags, SockType, SOCK_TYPE_MASK};
@@ -0,0 +1,54 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::RawSocketOption;
+use crate::{
+    impl_raw_socket_option, net::socket::ip::options::V6Only, prelude::*,
+    util::net::options::SocketOption,
+};
+
+/// Socket options for IPv6 socket.
+///
+/// The raw definitions can be found at:
+/// https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/in6.h#L170
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, TryFromInt)]
+#[expect(non_camel_case_types)]
+#[expect(clippy::upper_case_acronyms)]
+pub enum CIpv6OptionName {
+    ADDRFORM = 1,
+    PKTINFO_2292 = 2,
+    HOPOPTS_2292 = 3,
+    DSTOPTS_2292 = 4,
+    RTHDR_2292 = 5,
+    PKTOPTIONS_2292 = 6,
+    CHECKSUM = 7,
+    HOPLIMIT_2292 = 8,
+    NEXTHOP = 9,
+    AUTHHDR = 10,
+    UNICAST_HOPS = 16,
+    MULTICAST_IF = 17,
+    MULTICAST_HOPS = 18,
+    MULTICAST_LOOP = 19,
+    ADD_MEMBERSHIP = 20,
+    DROP_MEMBERSHIP = 21,
+    ROUTER_ALERT = 22,
+    MTU_DISCOVER = 23,
+    MTU = 24,
+    RECVERR = 25,
+    V6ONLY = 26,
+    JOIN_ANYCAST = 27,
+    LEAVE_ANYCAST = 28,
+    MULTICAST_ALL = 29,
+    ROUTER_ALERT_ISOLATE = 30,
+    RECVERR_RFC4884 = 31,
+}
+
+pub fn new_ipv6_option(name: i32) -> Result<Box<dyn RawSocketOption>> {
+    let name = CIpv6OptionName::try_from(name).map_err(|_| Errno::ENOPROTOOPT)?;
+    match name {
+        CIpv6OptionName::V6ONLY => Ok(Box::new(V6Only::new())),
+        _ => return_errno_with_message!(Errno::ENOPROTOOPT, "unsupported ipv6 level option"),
+    }
+}
+
+impl_raw_socket_option!(V6Only);
@@ -56,17 +56,30 @@ use ip::new_ip_option;
 use crate::{net::socket::options::SocketOption, prelude::*};
 
 mod ip;
+mod ipv6;
+mod netlink;
+mod packet;
 mod socket;
 mod tcp;
 mod utils;
 
-use self::{socket::new_socket_option, tcp::new_tcp_option};
+use self::{
+    ipv6::new_ipv6_option, netlink::new_netlink_option, packet::new_packet_option,
+    socket::new_socket_option, tcp::new_tcp_option,
+};
 
 pub trait RawSocketOption: SocketOption {
     fn read_from_user(&mut self, addr: Vaddr, max_len: u32) -> Result<()>;
 
     fn write_to_user(&self, addr: Vaddr, max_len: u32) -> Result<usize>;
 
+    /// Reads the input of `getsockopt` from user space.
+    ///
+    /// Most options do not take any input when they are got, so this does nothing by default.
+    fn read_input_from_user(&mut self, _addr: Vaddr, _max_len: u32) -> Result<()> {
+        Ok(())
+    }
+
     fn as_sock_option_mut(&mut self) -> &mut dyn SocketOption;
 
     fn as_sock_option(&self) -> &dyn SocketOption;
@@ -130,6 +143,42 @@ macro_rules! impl_raw_sock_option_get_only {
     };
 }
 
+/// Impl `RawSocketOption` for a struct which is for only `getsockopt` and implements `SocketOption`,
+/// where `getsockopt` reads its input from the user space before writing the output.
+#[macro_export]
+macro_rules! impl_raw_sock_option_get_with_input {
+    ($option:ty) => {
+        impl RawSocketOption for $option {
+            fn read_from_user(&mut self, _addr: Vaddr, _max_len: u32) -> Result<()> {
+                return_errno_with_message!(Errno::ENOPROTOOPT, "the option is getter-only");
+            }
+
+            fn write_to_user(&self, addr: Vaddr, max_len: u32) -> Result<usize> {
+                use $crate::util::net::options::utils::WriteToUser;
+
+                let output = self.get().unwrap();
+                output.write_to_user(addr, max_len)
+            }
+
+            fn read_input_from_user(&mut self, addr: Vaddr, max_len: u32) -> Result<()> {
+                use $crate::util::net::options::utils::ReadFromUser;
+
+                let input = ReadFromUser::read_from_user(addr, max_len)?;
+                self.set(input);
+                Ok(())
+            }
+
+            fn as_sock_option_mut(&mut self) -> &mut dyn SocketOption {
+                self
+            }
+
+            fn as_sock_option(&self) -> &dyn SocketOption {
+                self
+            }
+        }
+    };
+}
+
 pub fn new_raw_socket_option(
     level: CSocketOptionLevel,
     name: i32,
@@ -138,6 +187,9 @@ pub fn new_raw_socket_option(
         CSocketOptionLevel::SOL_SOCKET => new_socket_option(name),
         CSocketOptionLevel::SOL_IP => new_ip_option(name),
         CSocketOptionLevel::SOL_TCP => new_tcp_option(name),
+        CSocketOptionLevel::SOL_IPV6 => new_ipv6_option(name),
+        CSocketOptionLevel::SOL_PACKET => new_packet_option(name),
+        CSocketOptionLevel::SOL_NETLINK => new_netlink_option(name),
         _ => return_errno_with_message!(Errno::EOPNOTSUPP, "unsupported option level"),
     }
 }
@@ -153,4 +205,6 @@ pub enum CSocketOptionLevel {
     SOL_UDP = 17,
     SOL_IPV6 = 41,
     SOL_RAW = 255,
+    SOL_PACKET = 263,
+    SOL_NETLINK = 270,
 }
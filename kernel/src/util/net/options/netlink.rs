@@ -0,0 +1,43 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::RawSocketOption;
+use crate::{
+    impl_raw_socket_option,
+    net::socket::netlink::{AddMembership, DropMembership},
+    prelude::*,
+    util::net::options::SocketOption,
+};
+
+/// Socket options for netlink socket.
+///
+/// The raw definition is from https://elixir.bootlin.com/linux/v6.0.9/source/include/uapi/linux/netlink.h#L149
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, TryFromInt)]
+#[expect(non_camel_case_types)]
+#[expect(clippy::upper_case_acronyms)]
+pub enum CNetlinkOptionName {
+    ADD_MEMBERSHIP = 1,
+    DROP_MEMBERSHIP = 2,
+    PKTINFO = 3,
+    BROADCAST_ERROR = 4,
+    NO_ENOBUFS = 5,
+    RX_RING = 6,
+    TX_RING = 7,
+    LISTEN_ALL_NSID = 8,
+    LIST_MEMBERSHIPS = 9,
+    CAP_ACK = 10,
+    EXT_ACK = 11,
+    GET_STRICT_CHK = 12,
+}
+
+pub fn new_netlink_option(name: i32) -> Result<Box<dyn RawSocketOption>> {
+    let name = CNetlinkOptionName::try_from(name).map_err(|_| Errno::ENOPROTOOPT)?;
+    match name {
+        CNetlinkOptionName::ADD_MEMBERSHIP => Ok(Box::new(AddMembership::new())),
+        CNetlinkOptionName::DROP_MEMBERSHIP => Ok(Box::new(DropMembership::new())),
+        _ => return_errno_with_message!(Errno::ENOPROTOOPT, "unsupported netlink-level option"),
+    }
+}
+
+impl_raw_socket_option!(AddMembership);
+impl_raw_socket_option!(DropMembership);
@@ -4,7 +4,8 @@ use super::RawSocketOption;
 use crate::{
     impl_raw_sock_option_get_only, impl_raw_socket_option,
     net::socket::options::{
-        Error, KeepAlive, Linger, RecvBuf, ReuseAddr, ReusePort, SendBuf, SocketOption,
+        Error, KeepAlive, Linger, PassCred, PeerCred, RecvBuf, ReuseAddr, ReusePort, SendBuf,
+        SocketOption, Timestamp, TimestampNs,
     },
     prelude::*,
 };
@@ -34,6 +35,12 @@ enum CSocketOptionName {
     LINGER = 13,
     BSDCOMPAT = 14,
     REUSEPORT = 15,
+    PASSCRED = 16,
+    PEERCRED = 17,
+    TIMESTAMP_OLD = 29,
+    TIMESTAMPNS_OLD = 35,
+    TIMESTAMP_NEW = 63,
+    TIMESTAMPNS_NEW = 64,
     RCVTIMEO_NEW = 66,
     SNDTIMEO_NEW = 67,
 }
@@ -48,6 +55,15 @@ pub fn new_socket_option(name: i32) -> Result<Box<dyn RawSocketOption>> {
         CSocketOptionName::REUSEPORT => Ok(Box::new(ReusePort::new())),
         CSocketOptionName::LINGER => Ok(Box::new(Linger::new())),
         CSocketOptionName::KEEPALIVE => Ok(Box::new(KeepAlive::new())),
+        CSocketOptionName::PASSCRED => Ok(Box::new(PassCred::new())),
+        CSocketOptionName::PEERCRED => Ok(Box::new(PeerCred::new())),
+        // On 64-bit architectures, the old and new timestamp formats are the same.
+        CSocketOptionName::TIMESTAMP_OLD | CSocketOptionName::TIMESTAMP_NEW => {
+            Ok(Box::new(Timestamp::new()))
+        }
+        CSocketOptionName::TIMESTAMPNS_OLD | CSocketOptionName::TIMESTAMPNS_NEW => {
+            Ok(Box::new(TimestampNs::new()))
+        }
         _ => return_errno_with_message!(Errno::ENOPROTOOPT, "unsupported socket-level option"),
     }
 }
@@ -59,3 +75,7 @@ impl_raw_sock_option_get_only!(Error);
 impl_raw_socket_option!(ReusePort);
 impl_raw_socket_option!(Linger);
 impl_raw_socket_option!(KeepAlive);
+impl_raw_socket_option!(Timestamp);
+impl_raw_socket_option!(TimestampNs);
+impl_raw_socket_option!(PassCred);
+impl_raw_sock_option_get_only!(PeerCred);
@@ -6,6 +6,8 @@ use crate::{
     current_userspace,
     net::socket::{
         ip::{options::IpTtl, stream::CongestionControl},
+        packet::{PacketMreq, TpacketReq, TpacketStats},
+        unix::UnixCredentials,
         LingerOption,
     },
     prelude::*,
@@ -35,8 +37,9 @@ pub trait WriteToUser {
     fn write_to_user(&self, addr: Vaddr, max_len: u32) -> Result<usize>;
 }
 
-/// This macro is used to implement `ReadFromUser` and `WriteToUser` for u32 and i32.
-macro_rules! impl_read_write_for_32bit_type {
+/// This macro is used to implement `ReadFromUser` and `WriteToUser` for u32, i32, and C structs
+/// whose kernel types are the same as their user types.
+macro_rules! impl_read_write_for_pod_type {
     ($pod_ty: ty) => {
         impl ReadFromUser for $pod_ty {
             fn read_from_user(addr: Vaddr, max_len: u32) -> Result<Self> {
@@ -62,8 +65,11 @@ macro_rules! impl_read_write_for_32bit_type {
     };
 }
 
-impl_read_write_for_32bit_type!(i32);
-impl_read_write_for_32bit_type!(u32);
+impl_read_write_for_pod_type!(i32);
+impl_read_write_for_pod_type!(u32);
+impl_read_write_for_pod_type!(PacketMreq);
+impl_read_write_for_pod_type!(TpacketReq);
+impl_read_write_for_pod_type!(TpacketStats);
 
 impl ReadFromUser for bool {
     fn read_from_user(addr: Vaddr, max_len: u32) -> Result<Self> {
@@ -162,6 +168,17 @@ impl WriteToUser for LingerOption {
     }
 }
 
+impl WriteToUser for UnixCredentials {
+    fn write_to_user(&self, addr: Vaddr, max_len: u32) -> Result<usize> {
+        // Like Linux, the credentials are truncated if the buffer is too short.
+        let write_len = size_of::<UnixCredentials>().min(max_len as usize);
+
+        current_userspace!()
+            .write_bytes(addr, &mut VmReader::from(&self.as_bytes()[..write_len]))?;
+        Ok(write_len)
+    }
+}
+
 const TCP_CONGESTION_NAME_MAX: u32 = 16;
 
 impl ReadFromUser for CongestionControl {
@@ -1,8 +1,12 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use super::read_socket_addr_from_user;
+use super::{
+    control_message::{read_control_messages_from_user, write_control_messages_to_user},
+    read_socket_addr_from_user,
+};
 use crate::{
-    net::socket::SocketAddr,
+    fs::file_table::FdFlags,
+    net::socket::{ControlMessage, SendRecvFlags, SocketAddr},
     prelude::*,
     util::{net::write_socket_addr_with_max_len, VmReaderArray, VmWriterArray},
 };
@@ -24,6 +28,7 @@ pub enum Protocol {
     IPPROTO_TP = 29,        /* SO Transport Protocol Class 4	*/
     IPPROTO_DCCP = 33,      /* Datagram Congestion Control Protocol */
     IPPROTO_IPV6 = 41,      /* IPv6-in-IPv4 tunnelling		*/
+    IPPROTO_ICMPV6 = 58,    /* ICMPv6				*/
     IPPROTO_RSVP = 46,      /* RSVP Protocol			*/
     IPPROTO_GRE = 47,       /* Cisco GRE tunnels (rfc 1701,1702)	*/
     IPPROTO_ESP = 50,       /* Encapsulation Security Payload protocol */
@@ -84,11 +89,11 @@ pub struct CUserMsgHdr {
     /// Scatter/Gather iov array
     pub msg_iov: Vaddr,
     /// The # of elements in msg_iov
-    pub msg_iovlen: u32,
+    pub msg_iovlen: usize,
     /// Ancillary data
     pub msg_control: Vaddr,
     /// Ancillary data buffer length
-    pub msg_controllen: u32,
+    pub msg_controllen: usize,
     /// Flags on received message
     pub msg_flags: u32,
 }
@@ -103,12 +108,59 @@ impl CUserMsgHdr {
         Ok(Some(socket_addr))
     }
 
-    pub fn write_socket_addr_to_user(&self, addr: &SocketAddr) -> Result<()> {
+    /// Writes the socket address to the user space and updates `msg_namelen` accordingly.
+    pub fn write_socket_addr_to_user(&mut self, addr: &SocketAddr) -> Result<()> {
         if self.msg_name == 0 {
             return Ok(());
         }
 
-        write_socket_addr_with_max_len(addr, self.msg_name, self.msg_namelen)?;
+        self.msg_namelen = write_socket_addr_with_max_len(addr, self.msg_name, self.msg_namelen)?;
+        Ok(())
+    }
+
+    pub fn read_control_messages_from_user(&self, ctx: &Context) -> Result<Vec<ControlMessage>> {
+        if self.msg_control == 0 {
+            return Ok(Vec::new());
+        }
+
+        read_control_messages_from_user(self.msg_control, self.msg_controllen, ctx)
+    }
+
+    /// Writes the control messages to the user space and updates `msg_controllen` and
+    /// `msg_flags` accordingly.
+    ///
+    /// The files passed via `SCM_RIGHTS` are installed in the file table, so the caller must
+    /// not hold the file table.
+    pub fn write_control_messages_to_user(
+        &mut self,
+        control_messages: &[ControlMessage],
+        flags: SendRecvFlags,
+        ctx: &Context,
+    ) -> Result<()> {
+        let max_len = if self.msg_control == 0 {
+            0
+        } else {
+            self.msg_controllen
+        };
+
+        let fd_flags = if flags.contains(SendRecvFlags::MSG_CMSG_CLOEXEC) {
+            FdFlags::CLOEXEC
+        } else {
+            FdFlags::empty()
+        };
+
+        let (written_len, is_truncated) = write_control_messages_to_user(
+            control_messages,
+            self.msg_control,
+            max_len,
+            fd_flags,
+            ctx,
+        )?;
+
+        self.msg_controllen = written_len;
+        if is_truncated {
+            self.msg_flags |= SendRecvFlags::MSG_CTRUNC.bits() as u32;
+        }
         Ok(())
     }
 
@@ -116,13 +168,13 @@ impl CUserMsgHdr {
         &self,
         user_space: &'a CurrentUserSpace<'a>,
     ) -> Result<VmReaderArray<'a>> {
-        VmReaderArray::from_user_io_vecs(user_space, self.msg_iov, self.msg_iovlen as usize)
+        VmReaderArray::from_user_io_vecs(user_space, self.msg_iov, self.msg_iovlen)
     }
 
     pub fn copy_writer_array_from_user<'a>(
         &self,
         user_space: &'a CurrentUserSpace<'a>,
     ) -> Result<VmWriterArray<'a>> {
-        VmWriterArray::from_user_io_vecs(user_space, self.msg_iov, self.msg_iovlen as usize)
+        VmWriterArray::from_user_io_vecs(user_space, self.msg_iov, self.msg_iovlen)
     }
 }
@@ -1,19 +1,24 @@
 // SPDX-License-Identifier: MPL-2.0
 
-#![expect(unused_variables)]
+//! The cryptographically secure random number generator.
+//!
+//! The generator is HMAC_DRBG with SHA-256, as specified in NIST SP 800-90A, Section 10.1.2.
+//! The hash function is looked up from the crypto API, so that the accelerated implementation
+//! is used if it is registered.
 
-use rand::{rngs::StdRng, Error as RandError, RngCore};
+use aster_crypto::api::{self, Hash, Hmac};
 use spin::Once;
 
 use crate::prelude::*;
 
-static RNG: Once<SpinLock<StdRng>> = Once::new();
+static RNG: Once<SpinLock<HmacDrbg>> = Once::new();
 
 /// Fill `dest` with random bytes.
 ///
-/// It's cryptographically secure, as documented in [`rand::rngs::StdRng`].
+/// It's cryptographically secure, as long as the seed is unpredictable.
 pub fn getrandom(dst: &mut [u8]) -> Result<()> {
-    Ok(RNG.get().unwrap().lock().try_fill_bytes(dst)?)
+    RNG.get().unwrap().lock().generate(dst);
+    Ok(())
 }
 
 pub fn init() {
@@ -21,39 +26,113 @@ pub fn init() {
 
     cfg_if::cfg_if! {
         if #[cfg(target_arch = "x86_64")] {
-            use rand::SeedableRng;
             use ostd::arch::read_random;
 
-            let mut seed = <StdRng as SeedableRng>::Seed::default();
-            let mut chunks = seed.as_mut().chunks_exact_mut(size_of::<u64>());
-            for chunk in chunks.by_ref() {
+            let mut seed = [0u8; SEED_LEN];
+            for chunk in seed.chunks_exact_mut(size_of::<u64>()) {
                 let src = read_random().expect("read_random failed multiple times").to_ne_bytes();
                 chunk.copy_from_slice(&src);
             }
-            let tail = chunks.into_remainder();
-            let n = tail.len();
-            if n > 0 {
-                let src = read_random().expect("read_random failed multiple times").to_ne_bytes();
-                tail.copy_from_slice(&src[..n]);
-            }
 
-            RNG.call_once(|| SpinLock::new(StdRng::from_seed(seed)));
+            RNG.call_once(|| SpinLock::new(HmacDrbg::new(&seed)));
         } else if #[cfg(target_arch = "riscv64")] {
-            use rand::SeedableRng;
             use ostd::arch::boot::DEVICE_TREE;
 
             let chosen = DEVICE_TREE.get().unwrap().find_node("/chosen").unwrap();
-            let seed = chosen.property("rng-seed").unwrap().value.try_into().unwrap();
+            let seed = chosen.property("rng-seed").unwrap().value;
+            assert!(seed.len() >= SEED_LEN, "the RNG seed is too short");
 
-            RNG.call_once(|| SpinLock::new(StdRng::from_seed(seed)));
+            RNG.call_once(|| SpinLock::new(HmacDrbg::new(seed)));
         } else {
             compile_error!("unsupported target");
         }
     }
 }
 
-impl From<RandError> for Error {
-    fn from(value: RandError) -> Self {
-        Error::with_message(Errno::ENOSYS, "cannot generate random bytes")
+/// The minimum length of the seed, which provides 256 bits of entropy.
+const SEED_LEN: usize = 32;
+
+/// The maximum number of bytes generated per request.
+const MAX_REQUEST_LEN: usize = 1 << 16;
+
+/// The output length of SHA-256.
+const OUT_LEN: usize = 32;
+
+struct HmacDrbg {
+    hash: Arc<dyn Hash>,
+    key: [u8; OUT_LEN],
+    value: [u8; OUT_LEN],
+}
+
+impl HmacDrbg {
+    /// Instantiates the generator with the seed.
+    fn new(seed: &[u8]) -> Self {
+        let hash = api::find_hash("sha256").unwrap();
+        debug_assert_eq!(hash.digest_len(), OUT_LEN);
+
+        let mut drbg = Self {
+            hash,
+            key: [0; OUT_LEN],
+            value: [1; OUT_LEN],
+        };
+        drbg.update(seed);
+        drbg
+    }
+
+    fn hmac(&self, parts: &[&[u8]]) -> [u8; OUT_LEN] {
+        let mut hmac = Hmac::new(self.hash.as_ref(), &self.key);
+        for part in parts {
+            hmac.update(part);
+        }
+
+        let mut mac = [0u8; OUT_LEN];
+        hmac.finalize(&mut mac);
+        mac
+    }
+
+    /// Updates the internal state with `provided`, which may be empty.
+    fn update(&mut self, provided: &[u8]) {
+        self.key = self.hmac(&[&self.value, &[0x00], provided]);
+        self.value = self.hmac(&[&self.value]);
+
+        if provided.is_empty() {
+            return;
+        }
+
+        self.key = self.hmac(&[&self.value, &[0x01], provided]);
+        self.value = self.hmac(&[&self.value]);
+    }
+
+    fn generate(&mut self, dst: &mut [u8]) {
+        for request in dst.chunks_mut(MAX_REQUEST_LEN) {
+            for chunk in request.chunks_mut(OUT_LEN) {
+                self.value = self.hmac(&[&self.value]);
+                chunk.copy_from_slice(&self.value[..chunk.len()]);
+            }
+            // Backtracking resistance: the outputs cannot be recovered from the new state.
+            self.update(&[]);
+        }
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::*;
+
+    use super::*;
+
+    #[ktest]
+    fn hmac_drbg_is_deterministic() {
+        let mut a = HmacDrbg::new(&[0x42; SEED_LEN]);
+        let mut b = HmacDrbg::new(&[0x42; SEED_LEN]);
+
+        let mut out_a = [0u8; 100];
+        let mut out_b = [0u8; 100];
+        a.generate(&mut out_a);
+        b.generate(&mut out_b);
+        assert_eq!(out_a, out_b);
+
+        b.generate(&mut out_b);
+        assert_ne!(out_a, out_b);
     }
 }
@@ -8,12 +8,15 @@ use core::{
 
 use align_ext::AlignExt;
 use inherit_methods_macro::inherit_methods;
-use ostd::mm::{FrameAllocOptions, Segment, UntypedMem, VmIo};
+use ostd::mm::{valloc::VAlloc, VmIo};
 
 use super::{MultiRead, MultiWrite};
 use crate::prelude::*;
 
-/// A lock-free SPSC FIFO ring buffer backed by a [`Segment<()>`].
+/// A lock-free SPSC FIFO ring buffer backed by a [`VAlloc`].
+///
+/// The buffer is virtually contiguous, so a large ring buffer does not need
+/// physically contiguous frames.
 ///
 /// The ring buffer supports `push`/`pop` any `T: Pod` items, also
 /// supports `write`/`read` any bytes data based on [`VmReader`]/[`VmWriter`].
@@ -46,7 +49,7 @@ use crate::prelude::*;
 /// }
 /// ```
 pub struct RingBuffer<T> {
-    segment: Segment<()>,
+    buffer: VAlloc,
     capacity: usize,
     tail: AtomicUsize,
     head: AtomicUsize,
@@ -77,13 +80,10 @@ impl<T> RingBuffer<T> {
             capacity.is_power_of_two(),
             "capacity must be a power of two"
         );
-        let nframes = capacity.saturating_mul(Self::T_SIZE).align_up(PAGE_SIZE) / PAGE_SIZE;
-        let segment = FrameAllocOptions::new()
-            .zeroed(false)
-            .alloc_segment(nframes)
-            .unwrap();
+        let buffer =
+            VAlloc::new(capacity.saturating_mul(Self::T_SIZE).align_up(PAGE_SIZE)).unwrap();
         Self {
-            segment,
+            buffer,
             capacity,
             tail: AtomicUsize::new(0),
             head: AtomicUsize::new(0),
@@ -261,9 +261,9 @@ impl<T: Pod, R: Deref<Target = RingBuffer<T>>> Producer<T, R> {
         let tail = rb.tail();
         debug_assert!(tail < rb.capacity);
 
-        let segment_offset = tail * Self::T_SIZE;
-        let mut writer = rb.segment.writer();
-        writer.skip(segment_offset);
+        let buffer_offset = tail * Self::T_SIZE;
+        let mut writer = rb.buffer.writer();
+        writer.skip(buffer_offset);
         writer.write_val(&item).unwrap();
 
         rb.advance_tail(tail, 1);
@@ -284,18 +284,18 @@ impl<T: Pod, R: Deref<Target = RingBuffer<T>>> Producer<T, R> {
 
         let tail = rb.tail();
         debug_assert!(tail < rb.capacity);
-        let segment_offset = tail * Self::T_SIZE;
+        let buffer_offset = tail * Self::T_SIZE;
 
         if tail + nitems > rb.capacity {
             // Write into two separate parts
-            rb.segment
-                .write_slice(segment_offset, &items[..rb.capacity - tail])
+            rb.buffer
+                .write_slice(buffer_offset, &items[..rb.capacity - tail])
                 .unwrap();
-            rb.segment
+            rb.buffer
                 .write_slice(0, &items[rb.capacity - tail..])
                 .unwrap();
         } else {
-            rb.segment.write_slice(segment_offset, items).unwrap();
+            rb.buffer.write_slice(buffer_offset, items).unwrap();
         }
 
         rb.advance_tail(tail, nitems);
@@ -318,16 +318,16 @@ impl<R: Deref<Target = RingBuffer<u8>>> Producer<u8, R> {
         let tail = rb.tail();
         let write_len = if tail + write_len > rb.capacity {
             // Write into two separate parts
-            let mut writer = rb.segment.writer();
+            let mut writer = rb.buffer.writer();
             writer.skip(tail).limit(rb.capacity - tail);
             let mut len = reader.read(&mut writer)?;
 
-            let mut writer = rb.segment.writer();
+            let mut writer = rb.buffer.writer();
             writer.limit(write_len - (rb.capacity - tail));
             len += reader.read(&mut writer)?;
             len
         } else {
-            let mut writer = rb.segment.writer();
+            let mut writer = rb.buffer.writer();
             writer.skip(tail).limit(write_len);
             reader.read(&mut writer)?
         };
@@ -362,9 +362,9 @@ impl<T: Pod, R: Deref<Target = RingBuffer<T>>> Consumer<T, R> {
         let head = rb.head();
         debug_assert!(head < rb.capacity);
 
-        let segment_offset = head * Self::T_SIZE;
-        let mut reader = rb.segment.reader();
-        reader.skip(segment_offset);
+        let buffer_offset = head * Self::T_SIZE;
+        let mut reader = rb.buffer.reader();
+        reader.skip(buffer_offset);
         let item = reader.read_val::<T>().unwrap();
 
         rb.advance_head(head, 1);
@@ -385,17 +385,17 @@ impl<T: Pod, R: Deref<Target = RingBuffer<T>>> Consumer<T, R> {
         let head = rb.head();
         debug_assert!(head < rb.capacity);
 
-        let segment_offset = head * Self::T_SIZE;
+        let buffer_offset = head * Self::T_SIZE;
         if head + nitems > rb.capacity {
             // Read from two separate parts
-            rb.segment
-                .read_slice(segment_offset, &mut items[..rb.capacity - head])
+            rb.buffer
+                .read_slice(buffer_offset, &mut items[..rb.capacity - head])
                 .unwrap();
-            rb.segment
+            rb.buffer
                 .read_slice(0, &mut items[rb.capacity - head..])
                 .unwrap();
         } else {
-            rb.segment.read_slice(segment_offset, items).unwrap();
+            rb.buffer.read_slice(buffer_offset, items).unwrap();
         }
 
         rb.advance_head(head, nitems);
@@ -418,16 +418,16 @@ impl<R: Deref<Target = RingBuffer<u8>>> Consumer<u8, R> {
         let head = rb.head();
         let read_len = if head + read_len > rb.capacity {
             // Read from two separate parts
-            let mut reader = rb.segment.reader();
+            let mut reader = rb.buffer.reader();
             reader.skip(head).limit(rb.capacity - head);
             let mut len = writer.write(&mut reader)?;
 
-            let mut reader = rb.segment.reader();
+            let mut reader = rb.buffer.reader();
             reader.limit(read_len - (rb.capacity - head));
             len += writer.write(&mut reader)?;
             len
         } else {
-            let mut reader = rb.segment.reader();
+            let mut reader = rb.buffer.reader();
             reader.skip(head).limit(read_len);
             writer.write(&mut reader)?
         };
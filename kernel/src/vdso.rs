@@ -30,7 +30,7 @@ use spin::Once;
 use crate::{
     fs::fs_resolver::{FsPath, FsResolver, AT_FDCWD},
     syscall::ClockId,
-    time::{clocks::MonotonicClock, timer::Timeout, SystemTime, START_TIME},
+    time::{clocks::MonotonicClock, timekeeping, timer::Timeout, SystemTime, START_TIME},
     vm::vmo::{Vmo, VmoOptions},
 };
 
@@ -161,29 +161,40 @@ impl VdsoData {
 
     fn update_high_res_instant(&mut self, instant: Instant, instant_cycles: u64) {
         self.last_cycles = instant_cycles;
+        let raw = Duration::new(instant.secs(), instant.nanos());
+        let adjusted = timekeeping::adjusted_at(raw);
         for clock_id in HIGH_RES_CLOCK_IDS {
-            let secs = if clock_id == ClockId::CLOCK_REALTIME {
-                instant.secs() + START_SECS_COUNT.get().unwrap()
-            } else {
-                instant.secs()
+            // The clocks are adjusted at the instant, and advance at the raw rate until the next
+            // update.
+            let time = match clock_id {
+                ClockId::CLOCK_REALTIME => adjusted.real,
+                ClockId::CLOCK_MONOTONIC => adjusted.monotonic,
+                ClockId::CLOCK_BOOTTIME => adjusted.boot,
+                _ => raw,
             };
 
             self.update_clock_instant(
                 clock_id as usize,
-                secs,
-                (instant.nanos() as u64) << self.shift as u64,
+                time.as_secs(),
+                (time.subsec_nanos() as u64) << self.shift as u64,
             );
         }
     }
 
     fn update_coarse_res_instant(&mut self, instant: Instant) {
+        let raw = Duration::new(instant.secs(), instant.nanos());
+        let adjusted = timekeeping::adjusted_at(raw);
         for clock_id in COARSE_RES_CLOCK_IDS {
-            let secs = if clock_id == ClockId::CLOCK_REALTIME_COARSE {
-                instant.secs() + START_SECS_COUNT.get().unwrap()
+            let time = if clock_id == ClockId::CLOCK_REALTIME_COARSE {
+                adjusted.real
             } else {
-                instant.secs()
+                adjusted.monotonic
             };
-            self.update_clock_instant(clock_id as usize, secs, instant.nanos() as u64);
+            self.update_clock_instant(
+                clock_id as usize,
+                time.as_secs(),
+                time.subsec_nanos() as u64,
+            );
         }
     }
 }
@@ -0,0 +1,354 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The pools of huge pages for hugetlbfs and `MAP_HUGETLB`.
+//!
+//! Like Linux, the huge pages are reserved in advance, either by the `hugepages=` parameter on
+//! the kernel command line or by writing to `/proc/sys/vm/nr_hugepages`, so that the
+//! applications that explicitly manage huge pages (e.g., databases and DPDK) get physically
+//! contiguous memory even after the memory is fragmented.
+//!
+//! The mappings of huge pages reserve the pages when they are created, so that the pages are
+//! guaranteed to be available when they are faulted in. The mappings fail with `ENOMEM` if
+//! there are not enough free pages that are not reserved.
+//!
+//! The huge pages are mapped with base pages for now, since OSTD does not map huge pages in the
+//! user space yet. They are still physically contiguous, which is what DMA-capable
+//! applications rely on.
+//!
+//! Reference: <https://docs.kernel.org/admin-guide/mm/hugetlbpage.html>
+
+use core::ops::Range;
+
+use align_ext::AlignExt;
+use aster_rights::Full;
+use ostd::mm::{FrameAllocOptions, UFrame, UntypedMem};
+
+use crate::{
+    kcmdline::KCmdlineArg,
+    prelude::*,
+    vm::vmo::{Pager, Vmo, VmoFlags, VmoOptions},
+};
+
+/// The sizes of huge pages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HugePageSize {
+    Size2M,
+    Size1G,
+}
+
+impl HugePageSize {
+    pub const ALL: [Self; 2] = [Self::Size2M, Self::Size1G];
+
+    /// The default size, which is used unless another size is specified.
+    pub const DEFAULT: Self = Self::Size2M;
+
+    /// Returns the size of a huge page in bytes.
+    pub const fn bytes(&self) -> usize {
+        match self {
+            Self::Size2M => 2 << 20,
+            Self::Size1G => 1 << 30,
+        }
+    }
+
+    /// Returns the size with the base-2 logarithm of the bytes (e.g., in `MAP_HUGE_2MB`).
+    pub fn from_shift(shift: u32) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|size| size.bytes().trailing_zeros() == shift)
+    }
+
+    /// Returns the size with the bytes.
+    pub fn from_bytes(bytes: usize) -> Option<Self> {
+        Self::ALL.into_iter().find(|size| size.bytes() == bytes)
+    }
+
+    fn nr_base_pages(&self) -> usize {
+        self.bytes() / PAGE_SIZE
+    }
+
+    fn pool(&self) -> &'static HugePagePool {
+        static POOLS: [HugePagePool; 2] = [
+            HugePagePool::new(HugePageSize::Size2M),
+            HugePagePool::new(HugePageSize::Size1G),
+        ];
+        &POOLS[*self as usize]
+    }
+}
+
+/// A physically contiguous huge page, which consists of base pages.
+struct HugePage(Vec<UFrame>);
+
+impl HugePage {
+    fn alloc(size: HugePageSize) -> Result<Self> {
+        let segment = FrameAllocOptions::new().alloc_segment(size.nr_base_pages())?;
+        Ok(Self(segment.map(UFrame::from).collect()))
+    }
+
+    /// Returns whether the pages are not referenced elsewhere (e.g., by the page tables).
+    fn is_unused(&self) -> bool {
+        self.0.iter().all(|frame| frame.reference_count() == 1)
+    }
+
+    fn zero(&self) {
+        for frame in self.0.iter() {
+            frame.writer().fill(0u8);
+        }
+    }
+}
+
+/// A pool of huge pages of the same size.
+pub struct HugePagePool {
+    size: HugePageSize,
+    inner: Mutex<PoolInner>,
+}
+
+struct PoolInner {
+    /// The free pages, which are zeroed.
+    free: Vec<HugePage>,
+    /// The number of all the pages in the pool, including the pages in use.
+    nr_total: usize,
+    /// The number of the free pages that are reserved by the mappings.
+    nr_reserved: usize,
+}
+
+impl PoolInner {
+    fn nr_available(&self) -> usize {
+        self.free.len() - self.nr_reserved
+    }
+}
+
+/// The statistics of a pool of huge pages.
+#[derive(Debug, Clone, Copy)]
+pub struct HugePageStats {
+    pub nr_total: usize,
+    pub nr_free: usize,
+    pub nr_reserved: usize,
+}
+
+impl HugePagePool {
+    const fn new(size: HugePageSize) -> Self {
+        Self {
+            size,
+            inner: Mutex::new(PoolInner {
+                free: Vec::new(),
+                nr_total: 0,
+                nr_reserved: 0,
+            }),
+        }
+    }
+
+    /// Returns the pool of the huge pages of the size.
+    pub fn get(size: HugePageSize) -> &'static Self {
+        size.pool()
+    }
+
+    pub fn stats(&self) -> HugePageStats {
+        let inner = self.inner.lock();
+        HugePageStats {
+            nr_total: inner.nr_total,
+            nr_free: inner.free.len(),
+            nr_reserved: inner.nr_reserved,
+        }
+    }
+
+    /// Sets the number of the pages in the pool, and returns the number that is reached.
+    ///
+    /// Like Linux, the pool grows as far as the memory allows, and only shrinks by the pages
+    /// that are neither in use nor reserved.
+    pub fn set_nr_total(&self, nr_total: usize) -> usize {
+        let mut inner = self.inner.lock();
+        while inner.nr_total < nr_total {
+            let Ok(page) = HugePage::alloc(self.size) else {
+                break;
+            };
+            inner.free.push(page);
+            inner.nr_total += 1;
+        }
+        while inner.nr_total > nr_total && inner.nr_available() > 0 {
+            inner.free.pop();
+            inner.nr_total -= 1;
+        }
+        inner.nr_total
+    }
+
+    /// Reserves free pages for a mapping, or fails with `ENOMEM`.
+    fn reserve(&self, nr_pages: usize) -> Result<()> {
+        let mut inner = self.inner.lock();
+        if inner.nr_available() < nr_pages {
+            return_errno_with_message!(Errno::ENOMEM, "not enough free huge pages");
+        }
+        inner.nr_reserved += nr_pages;
+        Ok(())
+    }
+
+    fn unreserve(&self, nr_pages: usize) {
+        self.inner.lock().nr_reserved -= nr_pages;
+    }
+
+    /// Allocates a page, which consumes a reservation if `is_reserved` is true.
+    fn alloc(&self, is_reserved: bool) -> Result<HugePage> {
+        let mut inner = self.inner.lock();
+        if is_reserved {
+            inner.nr_reserved -= 1;
+        } else if inner.nr_available() == 0 {
+            return_errno_with_message!(Errno::ENOMEM, "not enough free huge pages");
+        }
+        Ok(inner.free.pop().unwrap())
+    }
+
+    /// Returns a page to the pool.
+    fn free(&self, page: HugePage) {
+        // The pages may still be mapped (e.g., after the file is truncated), in which case they
+        // cannot be reused and are replaced with new ones.
+        let page = if page.is_unused() {
+            page.zero();
+            Ok(page)
+        } else {
+            drop(page);
+            HugePage::alloc(self.size)
+        };
+
+        let mut inner = self.inner.lock();
+        match page {
+            Ok(page) => inner.free.push(page),
+            Err(_) => inner.nr_total -= 1,
+        }
+    }
+}
+
+/// Fills the pools as specified by the kernel command line.
+pub fn init(karg: &KCmdlineArg) {
+    for (size, nr_pages) in karg.get_hugepages() {
+        let Some(size) = HugePageSize::from_bytes(*size) else {
+            warn!("unsupported huge page size: {:#x}", size);
+            continue;
+        };
+        let nr_total = HugePagePool::get(size).set_nr_total(*nr_pages);
+        if nr_total < *nr_pages {
+            warn!(
+                "only {} of {} huge pages of {:#x} bytes are allocated",
+                nr_total,
+                nr_pages,
+                size.bytes()
+            );
+        }
+    }
+}
+
+/// The pager that provides the huge pages to a VMO.
+pub struct HugePagePager {
+    size: HugePageSize,
+    inner: Mutex<PagerInner>,
+}
+
+struct PagerInner {
+    /// The allocated pages, indexed by their indices in the VMO (in huge pages).
+    pages: BTreeMap<usize, HugePage>,
+    /// The indices of the pages that are reserved but not allocated yet.
+    reserved: BTreeSet<usize>,
+}
+
+impl HugePagePager {
+    fn new(size: HugePageSize) -> Arc<Self> {
+        Arc::new(Self {
+            size,
+            inner: Mutex::new(PagerInner {
+                pages: BTreeMap::new(),
+                reserved: BTreeSet::new(),
+            }),
+        })
+    }
+
+    pub fn page_size(&self) -> HugePageSize {
+        self.size
+    }
+
+    /// Reserves the pages in the range (in bytes) that are not allocated or reserved yet.
+    pub fn reserve(&self, range: &Range<usize>) -> Result<()> {
+        let mut inner = self.inner.lock();
+        let indices: Vec<_> = self
+            .huge_page_indices(range)
+            .filter(|idx| !inner.pages.contains_key(idx) && !inner.reserved.contains(idx))
+            .collect();
+
+        self.size.pool().reserve(indices.len())?;
+        inner.reserved.extend(indices);
+        Ok(())
+    }
+
+    /// Frees the pages and the reservations beyond `new_size` (in bytes).
+    pub fn truncate(&self, new_size: usize) {
+        let first_idx = new_size.div_ceil(self.size.bytes());
+
+        let mut inner = self.inner.lock();
+        let pages = inner.pages.split_off(&first_idx);
+        let reserved = inner.reserved.split_off(&first_idx);
+        drop(inner);
+
+        let pool = self.size.pool();
+        pool.unreserve(reserved.len());
+        for page in pages.into_values() {
+            pool.free(page);
+        }
+    }
+
+    fn huge_page_indices(&self, range: &Range<usize>) -> Range<usize> {
+        range.start / self.size.bytes()..range.end.div_ceil(self.size.bytes())
+    }
+}
+
+impl Pager for HugePagePager {
+    fn commit_page(&self, idx: usize) -> Result<UFrame> {
+        let nr_base_pages = self.size.nr_base_pages();
+        let huge_idx = idx / nr_base_pages;
+
+        let mut inner = self.inner.lock();
+        if !inner.pages.contains_key(&huge_idx) {
+            let is_reserved = inner.reserved.remove(&huge_idx);
+            let page = self.size.pool().alloc(is_reserved)?;
+            inner.pages.insert(huge_idx, page);
+        }
+        Ok(inner.pages[&huge_idx].0[idx % nr_base_pages].clone())
+    }
+
+    fn update_page(&self, _idx: usize) -> Result<()> {
+        Ok(())
+    }
+
+    fn decommit_page(&self, _idx: usize) -> Result<()> {
+        // The huge pages are freed as a whole in `truncate`.
+        Ok(())
+    }
+
+    fn commit_overwrite(&self, idx: usize) -> Result<UFrame> {
+        self.commit_page(idx)
+    }
+}
+
+impl Drop for HugePagePager {
+    fn drop(&mut self) {
+        self.truncate(0);
+    }
+}
+
+/// Allocates a VMO that is backed by the huge pages.
+///
+/// The size is rounded up to the size of the huge pages. If `reserve` is true, all the pages
+/// are reserved, or the allocation fails with `ENOMEM`.
+pub fn alloc_vmo(
+    page_size: HugePageSize,
+    size: usize,
+    reserve: bool,
+) -> Result<(Vmo<Full>, Arc<HugePagePager>)> {
+    let size = size.align_up(page_size.bytes());
+    let pager = HugePagePager::new(page_size);
+    if reserve {
+        pager.reserve(&(0..size))?;
+    }
+
+    let vmo = VmoOptions::<Full>::new(size)
+        .flags(VmoFlags::RESIZABLE)
+        .pager(pager.clone())
+        .alloc()?;
+    Ok((vmo, pager))
+}
@@ -19,6 +19,7 @@
 use osdk_frame_allocator::FrameAllocator;
 use osdk_heap_allocator::{type_from_layout, HeapAllocator};
 
+pub mod hugetlb;
 pub mod page_fault_handler;
 pub mod perms;
 pub mod util;
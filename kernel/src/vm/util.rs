@@ -1,6 +1,7 @@
 // SPDX-License-Identifier: MPL-2.0
 
 use ostd::mm::{Frame, FrameAllocOptions, UFrame, UntypedMem};
+use spin::Once;
 
 use crate::prelude::*;
 
@@ -12,3 +13,29 @@ pub fn duplicate_frame(src: &UFrame) -> Result<Frame<()>> {
     new_frame.writer().write(&mut src.reader());
     Ok(new_frame)
 }
+
+/// Returns the zero page, which is shared by all the read-only mappings of the untouched
+/// anonymous memory.
+///
+/// The zero page must never be written. It is mapped without the write permission, and is
+/// replaced with a new frame on the first write (see [`new_frame_for_cow`]).
+pub fn zero_page() -> &'static UFrame {
+    static ZERO_PAGE: Once<UFrame> = Once::new();
+
+    ZERO_PAGE.call_once(|| FrameAllocOptions::new().alloc_frame().unwrap().into())
+}
+
+/// Returns whether the frame is the zero page.
+pub fn is_zero_page(frame: &UFrame) -> bool {
+    frame.start_paddr() == zero_page().start_paddr()
+}
+
+/// Creates a new `Frame<()>` to replace the `src` on a copy-on-write fault.
+///
+/// The zero page needs no copy, since a zeroed frame is allocated.
+pub fn new_frame_for_cow(src: &UFrame) -> Result<Frame<()>> {
+    if is_zero_page(src) {
+        return Ok(FrameAllocOptions::new().alloc_frame()?);
+    }
+    duplicate_frame(src)
+}
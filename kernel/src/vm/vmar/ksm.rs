@@ -0,0 +1,473 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Kernel same-page merging (KSM).
+//!
+//! The anonymous private mappings that are advised with `MADV_MERGEABLE` are scanned by a
+//! kernel thread, which merges the pages of identical contents into shared KSM pages. The KSM
+//! pages are mapped without the write permission, so a write to them breaks out with a COW,
+//! like the pages that are shared after `fork`. The pages full of zeros are merged into the
+//! zero page.
+//!
+//! Like Linux, a page is merged only if its contents are not changed since the last scan, so
+//! that the volatile pages are not merged just to be broken out soon. Such pages are kept in an
+//! "unstable" table during a full scan, and are merged when another page of identical contents
+//! is found. The KSM pages are kept in a "stable" table until they are no longer mapped.
+//!
+//! The thread is controlled with the files in `/proc/sys/vm/ksm`, which work like
+//! `/sys/kernel/mm/ksm` in Linux.
+//!
+//! Reference: <https://docs.kernel.org/admin-guide/mm/ksm.html>
+
+use core::{
+    ops::Range,
+    sync::atomic::{AtomicU32, AtomicU64, Ordering},
+    time::Duration,
+};
+
+use ostd::{
+    mm::{
+        tlb::TlbFlushOp,
+        vm_space::{CursorMut, VmItem},
+        FrameAllocOptions, Paddr, PageFlags, PageProperty, UFrame, UntypedMem,
+    },
+    sync::{SpinLock, WaitQueue},
+};
+use spin::Once;
+
+use super::Vmar_;
+use crate::{
+    prelude::*,
+    sched::{Nice, SchedPolicy},
+    thread::kernel_thread::ThreadOptions,
+    vm::util::{duplicate_frame, is_zero_page, zero_page},
+    WaitTimeout,
+};
+
+/// The states of the KSM thread, which are written to `/proc/sys/vm/ksm/run`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromInt)]
+#[repr(u32)]
+pub enum RunState {
+    /// The thread stops scanning, but the merged pages are kept.
+    Stop = 0,
+    /// The thread scans the mergeable pages.
+    Run = 1,
+    /// The thread stops scanning, and all the merged pages are broken out.
+    Unmerge = 2,
+}
+
+static RUN_STATE: AtomicU32 = AtomicU32::new(RunState::Stop as u32);
+static PAGES_TO_SCAN: AtomicU32 = AtomicU32::new(100);
+static SLEEP_MILLISECS: AtomicU32 = AtomicU32::new(20);
+static FULL_SCANS: AtomicU64 = AtomicU64::new(0);
+
+/// The wait queue of the KSM thread, which is woken when the tunables are changed.
+static WAIT_QUEUE: WaitQueue = WaitQueue::new();
+
+/// The VMARs that have mergeable mappings, indexed by their addresses.
+static REGISTERED_VMARS: Mutex<BTreeMap<usize, Weak<Vmar_>>> = Mutex::new(BTreeMap::new());
+
+/// The KSM pages.
+static STABLE_PAGES: SpinLock<BTreeMap<Paddr, (u64, UFrame)>> = SpinLock::new(BTreeMap::new());
+
+/// Returns the state of the KSM thread.
+pub fn run_state() -> RunState {
+    RunState::try_from(RUN_STATE.load(Ordering::Relaxed)).unwrap()
+}
+
+/// Sets the state of the KSM thread.
+///
+/// The thread is spawned when it runs for the first time.
+pub fn set_run_state(state: RunState) -> Result<()> {
+    RUN_STATE.store(state as u32, Ordering::Relaxed);
+    if state == RunState::Unmerge {
+        unmerge_all()?;
+    }
+
+    static KSMD: Once<()> = Once::new();
+    if state == RunState::Run {
+        KSMD.call_once(|| {
+            ThreadOptions::new(|| Scanner::new().run())
+                .sched_policy(SchedPolicy::Fair(Nice::MAX))
+                .spawn();
+        });
+    }
+    WAIT_QUEUE.wake_all();
+    Ok(())
+}
+
+/// Returns the number of the pages to scan in each batch.
+pub fn pages_to_scan() -> u32 {
+    PAGES_TO_SCAN.load(Ordering::Relaxed)
+}
+
+/// Sets the number of the pages to scan in each batch, which must be positive.
+pub fn set_pages_to_scan(pages: u32) -> Result<()> {
+    if pages == 0 {
+        return_errno_with_message!(Errno::EINVAL, "the number of pages must be positive");
+    }
+    PAGES_TO_SCAN.store(pages, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Returns the time to sleep between the batches in milliseconds.
+pub fn sleep_millisecs() -> u32 {
+    SLEEP_MILLISECS.load(Ordering::Relaxed)
+}
+
+/// Sets the time to sleep between the batches in milliseconds.
+pub fn set_sleep_millisecs(millisecs: u32) {
+    SLEEP_MILLISECS.store(millisecs, Ordering::Relaxed);
+    WAIT_QUEUE.wake_all();
+}
+
+/// Returns the number of the times that all the mergeable pages have been scanned.
+pub fn full_scans() -> u64 {
+    FULL_SCANS.load(Ordering::Relaxed)
+}
+
+/// Returns the number of the KSM pages that are mapped, and the number of the other mappings
+/// that share them (i.e., the number of the pages that are saved).
+pub fn pages_shared_and_sharing() -> (usize, usize) {
+    let stable_pages = STABLE_PAGES.lock();
+    let mut shared = 0;
+    let mut sharing = 0;
+    for (_, page) in stable_pages.values() {
+        // One reference is held by the stable table.
+        let nr_mappings = page.reference_count() as usize - 1;
+        if nr_mappings > 0 {
+            shared += 1;
+            sharing += nr_mappings - 1;
+        }
+    }
+    (shared, sharing)
+}
+
+/// Registers the VMAR to be scanned.
+pub(super) fn register(vmar: &Arc<Vmar_>) {
+    REGISTERED_VMARS
+        .lock()
+        .entry(Arc::as_ptr(vmar) as usize)
+        .or_insert_with(|| Arc::downgrade(vmar));
+}
+
+fn is_ksm_page(frame: &UFrame) -> bool {
+    STABLE_PAGES.lock().contains_key(&frame.start_paddr())
+}
+
+/// Breaks out all the merged pages.
+fn unmerge_all() -> Result<()> {
+    let vmars: Vec<_> = REGISTERED_VMARS
+        .lock()
+        .values()
+        .filter_map(Weak::upgrade)
+        .collect();
+    for vmar in vmars {
+        vmar.unmerge(&(vmar.base..vmar.base + vmar.size))?;
+    }
+    Ok(())
+}
+
+impl Vmar_ {
+    /// Breaks out the merged pages in the range, by replacing them with private copies.
+    pub(super) fn unmerge(&self, range: &Range<Vaddr>) -> Result<()> {
+        let _inner = self.inner.read();
+        let mut cursor = self.vm_space.cursor_mut(range)?;
+
+        while cursor.virt_addr() < range.end {
+            let next_addr = match cursor.query()? {
+                VmItem::Mapped { va, frame, prop } => {
+                    if is_ksm_page(&frame) {
+                        // The copy stays read-only, and is made writable by the next write
+                        // fault since it is not shared.
+                        cursor.map(duplicate_frame(&frame)?.into(), prop);
+                        cursor.flusher().sync_tlb_flush();
+                        continue;
+                    }
+                    va + PAGE_SIZE
+                }
+                VmItem::NotMapped { va, len } => va + len,
+            };
+            if next_addr >= range.end {
+                break;
+            }
+            cursor.jump(next_addr)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the addresses of at most `max_pages` pages in the mergeable mappings, starting
+    /// from `start`.
+    fn mergeable_pages(&self, start: Vaddr, max_pages: usize) -> Vec<Vaddr> {
+        let inner = self.inner.read();
+        let mut pages = Vec::new();
+        for vm_mapping in inner.vm_mappings.find(&(start..self.base + self.size)) {
+            if !vm_mapping.is_mergeable() {
+                continue;
+            }
+            let range = vm_mapping.map_to_addr().max(start)..vm_mapping.map_end();
+            for addr in range.step_by(PAGE_SIZE) {
+                if pages.len() == max_pages {
+                    return pages;
+                }
+                pages.push(addr);
+            }
+        }
+        pages
+    }
+
+    /// Runs `op` with the page at `addr`, if it is mapped in a mergeable mapping and is not
+    /// merged yet.
+    fn with_unmerged_page<T>(
+        &self,
+        addr: Vaddr,
+        op: impl FnOnce(&mut CursorMut<'_, '_>, &UFrame, PageProperty) -> Result<T>,
+    ) -> Result<Option<T>> {
+        let inner = self.inner.read();
+        if !inner
+            .vm_mappings
+            .find_one(&addr)
+            .is_some_and(|vm_mapping| vm_mapping.is_mergeable())
+        {
+            return Ok(None);
+        }
+
+        let mut cursor = self.vm_space.cursor_mut(&(addr..addr + PAGE_SIZE))?;
+        let VmItem::Mapped { frame, prop, .. } = cursor.query()? else {
+            return Ok(None);
+        };
+        if is_zero_page(&frame) || is_ksm_page(&frame) {
+            return Ok(None);
+        }
+        op(&mut cursor, &frame, prop).map(Some)
+    }
+
+    /// Replaces the page at `addr` with the KSM page (or the zero page), if their contents are
+    /// identical.
+    fn merge_page(&self, addr: Vaddr, ksm_page: &UFrame) -> Result<bool> {
+        let is_merged = self.with_unmerged_page(addr, |cursor, frame, prop| {
+            Ok(replace_page(cursor, addr, frame, prop, ksm_page))
+        })?;
+        Ok(is_merged == Some(true))
+    }
+}
+
+/// Replaces the mapped `frame` with `new_frame`, if their contents are identical.
+///
+/// The page is write-protected before the contents are compared, so that the contents cannot
+/// be changed by the user space during the comparison.
+fn replace_page(
+    cursor: &mut CursorMut<'_, '_>,
+    addr: Vaddr,
+    frame: &UFrame,
+    mut prop: PageProperty,
+    new_frame: &UFrame,
+) -> bool {
+    let was_writable = prop.flags.contains(PageFlags::W);
+    if was_writable {
+        cursor.protect_next(PAGE_SIZE, |p| p.flags -= PageFlags::W);
+        cursor.flusher().issue_tlb_flush(TlbFlushOp::Address(addr));
+        cursor.flusher().dispatch_tlb_flush();
+        cursor.flusher().sync_tlb_flush();
+        cursor.jump(addr).unwrap();
+    }
+
+    if read_page(frame) != read_page(new_frame) {
+        if was_writable {
+            cursor.protect_next(PAGE_SIZE, |p| p.flags |= PageFlags::W);
+        }
+        return false;
+    }
+
+    prop.flags -= PageFlags::W;
+    cursor.map(new_frame.clone(), prop);
+    cursor.flusher().sync_tlb_flush();
+    true
+}
+
+fn read_page(frame: &UFrame) -> Vec<u8> {
+    let mut contents = vec![0u8; PAGE_SIZE];
+    frame
+        .reader()
+        .read(&mut VmWriter::from(contents.as_mut_slice()));
+    contents
+}
+
+/// Computes the checksum of the contents of a page (with the 64-bit FNV-1a hash).
+fn checksum(contents: &[u8]) -> u64 {
+    const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+    contents.iter().fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// A registered VMAR that is being scanned.
+struct ScannedVmar {
+    vmar: Weak<Vmar_>,
+    /// The checksums of the pages in the last full scan.
+    last_checksums: BTreeMap<Vaddr, u64>,
+    /// The checksums of the pages in the current full scan.
+    checksums: BTreeMap<Vaddr, u64>,
+}
+
+/// The states of the KSM thread.
+struct Scanner {
+    vmars: BTreeMap<usize, ScannedVmar>,
+    /// The VMAR that is being scanned, and the address to continue with.
+    cursor: Option<(usize, Vaddr)>,
+    /// The pages that are not changed since the last full scan, indexed by their checksums.
+    unstable_pages: BTreeMap<u64, (Weak<Vmar_>, Vaddr)>,
+}
+
+impl Scanner {
+    fn new() -> Self {
+        Self {
+            vmars: BTreeMap::new(),
+            cursor: None,
+            unstable_pages: BTreeMap::new(),
+        }
+    }
+
+    fn run(mut self) {
+        loop {
+            WAIT_QUEUE.wait_until(|| (run_state() == RunState::Run).then_some(()));
+
+            self.scan_batch(pages_to_scan() as usize);
+
+            let sleep_time = Duration::from_millis(sleep_millisecs() as u64);
+            let _ = WAIT_QUEUE.wait_until_or_timeout(
+                || (run_state() != RunState::Run).then_some(()),
+                &sleep_time,
+            );
+        }
+    }
+
+    /// Scans at most `nr_pages` pages.
+    fn scan_batch(&mut self, mut nr_pages: usize) {
+        while nr_pages > 0 {
+            let Some((key, start)) = self.cursor.or_else(|| self.start_full_scan()) else {
+                return;
+            };
+
+            let pages = match self.vmars.get(&key).and_then(|vmar| vmar.vmar.upgrade()) {
+                Some(vmar) => vmar.mergeable_pages(start, nr_pages),
+                None => Vec::new(),
+            };
+            if pages.len() < nr_pages {
+                // The VMAR is finished.
+                self.cursor = self
+                    .vmars
+                    .range(key + 1..)
+                    .next()
+                    .map(|(key, vmar)| (*key, vmar.vmar.upgrade().map_or(0, |vmar| vmar.base)));
+                if self.cursor.is_none() {
+                    FULL_SCANS.fetch_add(1, Ordering::Relaxed);
+                }
+            } else {
+                self.cursor = Some((key, pages.last().unwrap() + PAGE_SIZE));
+            }
+
+            nr_pages -= pages.len().max(1).min(nr_pages);
+            for addr in pages {
+                // The failures (e.g., out of memory) only stop merging the page.
+                let _ = self.scan_page(key, addr);
+            }
+        }
+    }
+
+    /// Starts a full scan of the registered VMARs, and returns the first one to scan.
+    fn start_full_scan(&mut self) -> Option<(usize, Vaddr)> {
+        self.unstable_pages.clear();
+
+        // The KSM pages that are no longer mapped are freed.
+        STABLE_PAGES
+            .lock()
+            .retain(|_, (_, page)| page.reference_count() > 1);
+
+        let mut registered_vmars = REGISTERED_VMARS.lock();
+        registered_vmars.retain(|_, vmar| vmar.strong_count() > 0);
+        self.vmars
+            .retain(|key, _| registered_vmars.contains_key(key));
+        for (key, vmar) in registered_vmars.iter() {
+            let scanned_vmar = self.vmars.entry(*key).or_insert_with(|| ScannedVmar {
+                vmar: vmar.clone(),
+                last_checksums: BTreeMap::new(),
+                checksums: BTreeMap::new(),
+            });
+            scanned_vmar.last_checksums = core::mem::take(&mut scanned_vmar.checksums);
+        }
+        drop(registered_vmars);
+
+        let (key, vmar) = self.vmars.iter().next()?;
+        Some((*key, vmar.vmar.upgrade().map_or(0, |vmar| vmar.base)))
+    }
+
+    fn scan_page(&mut self, key: usize, addr: Vaddr) -> Result<()> {
+        let scanned_vmar = self.vmars.get_mut(&key).unwrap();
+        let Some(vmar) = scanned_vmar.vmar.upgrade() else {
+            return Ok(());
+        };
+
+        let contents = vmar.with_unmerged_page(addr, |cursor, frame, prop| {
+            let contents = read_page(frame);
+            if contents.iter().all(|byte| *byte == 0) {
+                replace_page(cursor, addr, frame, prop, zero_page());
+                return Ok(None);
+            }
+
+            let checksum = checksum(&contents);
+            let stable_page = STABLE_PAGES
+                .lock()
+                .values()
+                .find(|(page_checksum, page)| {
+                    *page_checksum == checksum && read_page(page) == contents
+                })
+                .map(|(_, page)| page.clone());
+            if let Some(stable_page) = stable_page {
+                replace_page(cursor, addr, frame, prop, &stable_page);
+                return Ok(None);
+            }
+
+            Ok(Some((checksum, contents)))
+        })?;
+        let Some(Some((checksum, contents))) = contents else {
+            return Ok(());
+        };
+
+        // Only the pages that are not changed since the last full scan are merged.
+        scanned_vmar.checksums.insert(addr, checksum);
+        if scanned_vmar.last_checksums.get(&addr) != Some(&checksum) {
+            return Ok(());
+        }
+
+        let Some((other_vmar, other_addr)) = self.unstable_pages.remove(&checksum) else {
+            self.unstable_pages
+                .insert(checksum, (Arc::downgrade(&vmar), addr));
+            return Ok(());
+        };
+
+        // Both pages are merged into a new KSM page. If the other page has been changed, this
+        // page is kept in the unstable table instead.
+        let ksm_page: UFrame = FrameAllocOptions::new().zeroed(false).alloc_frame()?.into();
+        ksm_page
+            .writer()
+            .write(&mut VmReader::from(contents.as_slice()));
+
+        let is_other_merged = match other_vmar.upgrade() {
+            Some(other_vmar) => other_vmar.merge_page(other_addr, &ksm_page)?,
+            None => false,
+        };
+        if !is_other_merged {
+            self.unstable_pages
+                .insert(checksum, (Arc::downgrade(&vmar), addr));
+            return Ok(());
+        }
+
+        STABLE_PAGES
+            .lock()
+            .insert(ksm_page.start_paddr(), (checksum, ksm_page.clone()));
+        vmar.merge_page(addr, &ksm_page)?;
+        Ok(())
+    }
+}
@@ -4,14 +4,21 @@
 
 mod dyn_cap;
 mod interval_set;
+pub mod ksm;
 mod static_cap;
 pub mod vm_mapping;
 
-use core::{num::NonZeroUsize, ops::Range};
+use core::{
+    num::NonZeroUsize,
+    ops::Range,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
 use align_ext::AlignExt;
 use aster_rights::Rights;
-use ostd::mm::{tlb::TlbFlushOp, PageFlags, PageProperty, VmSpace, MAX_USERSPACE_VADDR};
+use ostd::mm::{
+    tlb::TlbFlushOp, Paddr, PageFlags, PageProperty, UFrame, VmItem, VmSpace, MAX_USERSPACE_VADDR,
+};
 
 use self::{
     interval_set::{Interval, IntervalSet},
@@ -20,8 +27,10 @@ use self::{
 use super::page_fault_handler::PageFaultHandler;
 use crate::{
     prelude::*,
-    process::{Process, ResourceType},
-    thread::exception::PageFaultInfo,
+    process::{
+        credentials::capabilities::CapSet, posix_thread::AsPosixThread, Process, ResourceType,
+    },
+    thread::{exception::PageFaultInfo, Thread},
     vm::{
         perms::VmPerms,
         vmo::{Vmo, VmoRightsOp},
@@ -102,6 +111,207 @@ impl<R> Vmar<R> {
     pub fn resize_mapping(&self, map_addr: Vaddr, old_size: usize, new_size: usize) -> Result<()> {
         self.0.resize_mapping(map_addr, old_size, new_size)
     }
+
+    /// Sets the mmap base, above which the free regions are preferred for
+    /// the mappings that are not placed at fixed addresses.
+    pub fn set_mmap_base(&self, mmap_base: Vaddr) {
+        self.0.inner.write().mmap_base = mmap_base;
+    }
+
+    /// Sets the mmap top, below which the free regions are allocated for
+    /// the mappings that are not placed at fixed addresses.
+    ///
+    /// This is used to keep the mappings of a 32-bit program in its address
+    /// space.
+    pub fn set_mmap_top(&self, mmap_top: Vaddr) {
+        self.0.inner.write().mmap_top = mmap_top;
+    }
+
+    /// Returns whether there is a mapping that starts at `map_addr` and maps `vmo` from the
+    /// beginning.
+    pub fn is_vmo_mapped_at<R2>(&self, map_addr: Vaddr, vmo: &Vmo<R2>) -> bool {
+        let inner = self.0.inner.read();
+        inner
+            .vm_mappings
+            .find_one(&map_addr)
+            .is_some_and(|vm_mapping| {
+                vm_mapping.map_to_addr() == map_addr
+                    && vm_mapping.vmo().is_some_and(|mapped_vmo| {
+                        Arc::ptr_eq(&mapped_vmo.vmo().0, &vmo.0) && mapped_vmo.offset() == 0
+                    })
+            })
+    }
+
+    /// Removes the mappings of `vmo` in the range.
+    ///
+    /// Only the mappings whose offsets in the VMO equal to their offsets from the start of the
+    /// range are removed. The other mappings in the range are untouched.
+    pub fn remove_vmo_mappings<R2>(&self, vmo: &Vmo<R2>, range: Range<Vaddr>) -> Result<()> {
+        let mut inner = self.0.inner.write();
+
+        let ranges_to_remove: Vec<_> = inner
+            .vm_mappings
+            .find(&range)
+            .filter(|vm_mapping| {
+                vm_mapping.vmo().is_some_and(|mapped_vmo| {
+                    Arc::ptr_eq(&mapped_vmo.vmo().0, &vmo.0)
+                        && mapped_vmo.offset() + range.start == vm_mapping.map_to_addr()
+                })
+            })
+            .map(|vm_mapping| get_intersected_range(&range, &vm_mapping.range()))
+            .collect();
+        for range in ranges_to_remove {
+            inner.alloc_free_region_exact_truncate(&self.0.vm_space, range.start, range.len())?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the key of the shared memory at `addr`.
+    ///
+    /// If `addr` is in a shared VMO-backed mapping, the memory can be shared with other
+    /// processes, possibly at different addresses. Then the memory is identified by the key,
+    /// which consists of the VMO and the offset in the VMO. Otherwise, `None` is returned.
+    pub fn shared_memory_key(&self, addr: Vaddr) -> Option<SharedMemoryKey> {
+        let inner = self.0.inner.read();
+        let vm_mapping = inner.vm_mappings.find_one(&addr)?;
+        if !vm_mapping.is_shared() {
+            return None;
+        }
+
+        let mapped_vmo = vm_mapping.vmo()?;
+        Some(SharedMemoryKey {
+            vmo: Arc::as_ptr(&mapped_vmo.vmo().0) as usize,
+            offset: mapped_vmo.offset() + (addr - vm_mapping.map_to_addr()),
+        })
+    }
+
+    /// Commits the page at `addr` as if it were accessed with `required_perms`, and returns
+    /// the frame that is mapped there.
+    ///
+    /// The frame is pinned by the returned handle, so it can be accessed even if the page is
+    /// unmapped afterwards. This is used to access the memory of another address space (e.g.,
+    /// by `process_vm_readv`), where the page faults cannot be handled by the accesses.
+    pub fn pin_page(&self, addr: Vaddr, required_perms: VmPerms) -> Result<UFrame> {
+        self.check_rights(required_perms.into())?;
+        self.0.pin_page(addr, required_perms)
+    }
+
+    /// Locks the pages in the range (i.e., `mlock`), which must be fully mapped.
+    ///
+    /// The pages are populated unless [`LockOption::OnFault`] is specified.
+    pub fn lock(&self, range: Range<Vaddr>, option: LockOption) -> Result<()> {
+        self.0.lock(range, option, false)
+    }
+
+    /// Unlocks the pages in the range (i.e., `munlock`), which must be fully mapped.
+    pub fn unlock(&self, range: Range<Vaddr>) -> Result<()> {
+        let mut inner = self.0.inner.write();
+        inner.check_fully_mapped(&range)?;
+        inner.set_locked(&range, false)
+    }
+
+    /// Locks all the pages that are currently mapped (i.e., `mlockall` with `MCL_CURRENT`).
+    pub fn lock_all(&self, option: LockOption) -> Result<()> {
+        self.0
+            .lock(self.base()..self.base() + self.size(), option, true)
+    }
+
+    /// Unlocks all the pages, and stops locking the future mappings (i.e., `munlockall`).
+    pub fn unlock_all(&self) -> Result<()> {
+        let mut inner = self.0.inner.write();
+        inner.lock_future = None;
+        inner.set_locked(&(self.base()..self.base() + self.size()), false)
+    }
+
+    /// Sets whether the future mappings are locked (i.e., `mlockall` with `MCL_FUTURE`).
+    pub fn set_lock_future(&self, option: Option<LockOption>) {
+        self.0.inner.write().lock_future = option;
+    }
+
+    /// Returns the size of the locked memory in bytes.
+    pub fn locked_size(&self) -> usize {
+        self.0.inner.read().locked_vm
+    }
+
+    /// Returns whether each page in the range is resident (i.e., `mincore`).
+    ///
+    /// A page is resident if it is mapped in the page table. The range must be fully mapped.
+    pub fn page_residency(&self, range: Range<Vaddr>) -> Result<Vec<bool>> {
+        self.0.page_residency(range)
+    }
+
+    /// Returns the status of each page in the range, or `None` if the page is not mapped in
+    /// the page table (i.e., `/proc/[pid]/pagemap`).
+    pub fn page_status(&self, range: Range<Vaddr>) -> Result<Vec<Option<PageStatus>>> {
+        self.0.page_status(range)
+    }
+
+    /// Sets whether the pages in the range can be merged by KSM (i.e., `MADV_MERGEABLE` and
+    /// `MADV_UNMERGEABLE`), which must be fully mapped.
+    ///
+    /// Only the anonymous private mappings are affected. The merged pages are broken out if
+    /// they are no longer mergeable.
+    pub fn set_mergeable(&self, range: Range<Vaddr>, is_mergeable: bool) -> Result<()> {
+        {
+            let mut inner = self.0.inner.write();
+            inner.check_fully_mapped(&range)?;
+            inner.set_mergeable(&range, is_mergeable)?;
+        }
+
+        if is_mergeable {
+            ksm::register(&self.0);
+            Ok(())
+        } else {
+            self.0.unmerge(&range)
+        }
+    }
+}
+
+/// The status of a page that is mapped in the page table.
+#[derive(Debug, Clone, Copy)]
+pub struct PageStatus {
+    pub paddr: Paddr,
+    /// Whether the page is a page of a VMO (e.g., a page of a file or of the shared memory),
+    /// instead of a private anonymous page.
+    pub is_vmo_page: bool,
+    /// Whether the page is mapped only once.
+    pub is_exclusive: bool,
+}
+
+/// The options to lock the pages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockOption {
+    /// Populates the pages when they are locked.
+    Populate,
+    /// Locks the pages when they are populated by the page faults (i.e., `MLOCK_ONFAULT`).
+    OnFault,
+}
+
+/// The total size of the locked memory of all the VMARs in bytes.
+static TOTAL_LOCKED_VM: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns the total size of the locked memory in bytes, which is reported in
+/// `/proc/meminfo`.
+pub fn total_locked_size() -> usize {
+    TOTAL_LOCKED_VM.load(Ordering::Relaxed)
+}
+
+/// The key that identifies a location in the shared memory.
+///
+/// The key is only valid when the memory is mapped, since the address of a VMO may be reused
+/// after the VMO is dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SharedMemoryKey {
+    vmo: usize,
+    offset: usize,
+}
+
+impl SharedMemoryKey {
+    /// Returns a hash value of the key.
+    pub fn hash(&self) -> usize {
+        (self.vmo >> 4) ^ self.offset
+    }
 }
 
 pub(super) struct Vmar_ {
@@ -120,6 +330,14 @@ struct VmarInner {
     vm_mappings: IntervalSet<Vaddr, VmMapping>,
     /// The total mapped memory in bytes.
     total_vm: usize,
+    /// The total locked memory in bytes.
+    locked_vm: usize,
+    /// How the future mappings are locked, or `None` if they are not locked.
+    lock_future: Option<LockOption>,
+    /// The lowest address of the free regions that are preferred for new mappings.
+    mmap_base: Vaddr,
+    /// The end of the free regions that can be allocated for new mappings.
+    mmap_top: Vaddr,
 }
 
 impl VmarInner {
@@ -127,6 +345,10 @@ impl VmarInner {
         Self {
             vm_mappings: IntervalSet::new(),
             total_vm: 0,
+            locked_vm: 0,
+            lock_future: None,
+            mmap_base: ROOT_VMAR_LOWEST_ADDR,
+            mmap_top: ROOT_VMAR_CAP_ADDR,
         }
     }
 
@@ -154,11 +376,47 @@ impl VmarInner {
         Ok(())
     }
 
+    /// Returns `Ok` if the calling process may lock `lock_size` more bytes of its memory.
+    ///
+    /// Like Linux, this fails with `EPERM` if `RLIMIT_MEMLOCK` is zero, and with `ENOMEM` if
+    /// the limit is exceeded, unless the calling thread has `CAP_IPC_LOCK`.
+    fn check_lock_size(&self, lock_size: usize) -> Result<()> {
+        let (Some(process), Some(thread)) = (Process::current(), Thread::current()) else {
+            return Ok(());
+        };
+        let posix_thread = thread.as_posix_thread().unwrap();
+        if posix_thread
+            .credentials()
+            .effective_capset()
+            .contains(CapSet::IPC_LOCK)
+        {
+            return Ok(());
+        }
+
+        let rlimit_memlock = process
+            .resource_limits()
+            .get_rlimit(ResourceType::RLIMIT_MEMLOCK)
+            .get_cur();
+        if rlimit_memlock == 0 {
+            return_errno_with_message!(Errno::EPERM, "locking memory is not permitted");
+        }
+
+        let new_locked_vm = self.locked_vm.checked_add(lock_size).ok_or(Errno::ENOMEM)?;
+        if new_locked_vm as u64 > rlimit_memlock {
+            return_errno_with_message!(Errno::ENOMEM, "locked memory limit overflow");
+        }
+        Ok(())
+    }
+
     /// Inserts a `VmMapping` into the `Vmar`.
     ///
     /// Make sure the insertion doesn't exceed address space limit.
     fn insert(&mut self, vm_mapping: VmMapping) {
         self.total_vm += vm_mapping.map_size();
+        if vm_mapping.is_locked() {
+            self.locked_vm += vm_mapping.map_size();
+            TOTAL_LOCKED_VM.fetch_add(vm_mapping.map_size(), Ordering::Relaxed);
+        }
         self.vm_mappings.insert(vm_mapping);
     }
 
@@ -166,9 +424,86 @@ impl VmarInner {
     fn remove(&mut self, key: &Vaddr) -> Option<VmMapping> {
         let vm_mapping = self.vm_mappings.remove(key)?;
         self.total_vm -= vm_mapping.map_size();
+        if vm_mapping.is_locked() {
+            self.locked_vm -= vm_mapping.map_size();
+            TOTAL_LOCKED_VM.fetch_sub(vm_mapping.map_size(), Ordering::Relaxed);
+        }
         Some(vm_mapping)
     }
 
+    /// Returns `Ok` if the range is fully mapped, or fails with `ENOMEM`.
+    fn check_fully_mapped(&self, range: &Range<Vaddr>) -> Result<()> {
+        if self.count_overlap_size(range.clone()) != range.len() {
+            return_errno_with_message!(Errno::ENOMEM, "the range is not fully mapped");
+        }
+        Ok(())
+    }
+
+    /// Returns the size of the memory in the range that is not locked.
+    fn count_unlocked_size(&self, range: &Range<Vaddr>) -> usize {
+        self.vm_mappings
+            .find(range)
+            .filter(|vm_mapping| !vm_mapping.is_locked())
+            .map(|vm_mapping| get_intersected_range(range, &vm_mapping.range()).len())
+            .sum()
+    }
+
+    /// Locks or unlocks the mappings in the range, splitting the mappings if needed.
+    fn set_locked(&mut self, range: &Range<Vaddr>, is_locked: bool) -> Result<()> {
+        let mappings_to_change: Vec<_> = self
+            .vm_mappings
+            .find(range)
+            .filter(|vm_mapping| vm_mapping.is_locked() != is_locked)
+            .map(|vm_mapping| vm_mapping.map_to_addr())
+            .collect();
+
+        for vm_mapping_addr in mappings_to_change {
+            let vm_mapping = self.remove(&vm_mapping_addr).unwrap();
+            let intersected_range = get_intersected_range(range, &vm_mapping.range());
+
+            let (left, taken, right) = vm_mapping.split_range(&intersected_range)?;
+            self.insert(taken.set_locked(is_locked));
+            if let Some(left) = left {
+                self.insert(left);
+            }
+            if let Some(right) = right {
+                self.insert(right);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sets whether the mappings in the range are mergeable, splitting the mappings if needed.
+    ///
+    /// The mappings that cannot be mergeable are skipped.
+    fn set_mergeable(&mut self, range: &Range<Vaddr>, is_mergeable: bool) -> Result<()> {
+        let mappings_to_change: Vec<_> = self
+            .vm_mappings
+            .find(range)
+            .filter(|vm_mapping| {
+                vm_mapping.is_mergeable() != is_mergeable && vm_mapping.can_be_mergeable()
+            })
+            .map(|vm_mapping| vm_mapping.map_to_addr())
+            .collect();
+
+        for vm_mapping_addr in mappings_to_change {
+            let vm_mapping = self.remove(&vm_mapping_addr).unwrap();
+            let intersected_range = get_intersected_range(range, &vm_mapping.range());
+
+            let (left, taken, right) = vm_mapping.split_range(&intersected_range)?;
+            self.insert(taken.set_mergeable(is_mergeable));
+            if let Some(left) = left {
+                self.insert(left);
+            }
+            if let Some(right) = right {
+                self.insert(right);
+            }
+        }
+
+        Ok(())
+    }
+
     /// Calculates the total amount of overlap between `VmMapping`s
     /// and the provided range.
     fn count_overlap_size(&self, range: Range<Vaddr>) -> usize {
@@ -234,45 +569,64 @@ impl VmarInner {
 
     /// Allocates a free region for mapping.
     ///
-    /// If no such region is found, return an error.
+    /// The free regions above the mmap base are preferred. If no such region
+    /// is found, return an error.
     fn alloc_free_region(&mut self, size: usize, align: usize) -> Result<Range<Vaddr>> {
+        self.find_free_region(self.mmap_base, size, align)
+            .or_else(|| self.find_free_region(ROOT_VMAR_LOWEST_ADDR, size, align))
+            .ok_or_else(|| {
+                Error::with_message(Errno::ENOMEM, "Cannot find free region for mapping")
+            })
+    }
+
+    /// Finds a free region for mapping that starts at or above `lowest`.
+    fn find_free_region(&self, lowest: Vaddr, size: usize, align: usize) -> Option<Range<Vaddr>> {
         // Fast path that there's still room to the end.
         let highest_occupied = self
             .vm_mappings
             .iter()
             .next_back()
-            .map_or(ROOT_VMAR_LOWEST_ADDR, |vm_mapping| vm_mapping.range().end);
+            .map_or(lowest, |vm_mapping| vm_mapping.range().end.max(lowest));
         // FIXME: The up-align may overflow.
         let last_occupied_aligned = highest_occupied.align_up(align);
         if let Some(last) = last_occupied_aligned.checked_add(size) {
-            if last <= ROOT_VMAR_CAP_ADDR {
-                return Ok(last_occupied_aligned..last);
+            if last <= self.mmap_top {
+                return Some(last_occupied_aligned..last);
             }
         }
 
         // Slow path that we need to search for a free region.
         // Here, we use a simple brute-force FIRST-FIT algorithm.
         // Allocate as low as possible to reduce fragmentation.
-        let mut last_end: Vaddr = ROOT_VMAR_LOWEST_ADDR;
+        let mut last_end: Vaddr = lowest;
         for vm_mapping in self.vm_mappings.iter() {
             let range = vm_mapping.range();
+            if range.end <= last_end {
+                continue;
+            }
 
-            debug_assert!(range.start >= last_end);
             debug_assert!(range.end <= highest_occupied);
 
             let last_aligned = last_end.align_up(align);
-            let needed_end = last_aligned
-                .checked_add(size)
-                .ok_or(Error::new(Errno::ENOMEM))?;
+            let needed_end = last_aligned.checked_add(size)?;
+            if needed_end > self.mmap_top {
+                return None;
+            }
 
             if needed_end <= range.start {
-                return Ok(last_aligned..needed_end);
+                return Some(last_aligned..needed_end);
             }
 
             last_end = range.end;
         }
 
-        return_errno_with_message!(Errno::ENOMEM, "Cannot find free region for mapping");
+        None
+    }
+}
+
+impl Drop for VmarInner {
+    fn drop(&mut self) {
+        TOTAL_LOCKED_VM.fetch_sub(self.locked_vm, Ordering::Relaxed);
     }
 }
 
@@ -367,11 +721,122 @@ impl Vmar_ {
         return_errno_with_message!(Errno::EACCES, "page fault addr is not in current vmar");
     }
 
+    fn pin_page(&self, address: Vaddr, required_perms: VmPerms) -> Result<UFrame> {
+        let page_addr = address.align_down(PAGE_SIZE);
+
+        // The page may be unmapped or write-protected again (e.g., by a concurrent `fork`)
+        // after the page fault is handled, in which case the page fault is handled again.
+        loop {
+            self.handle_page_fault(&PageFaultInfo {
+                address,
+                required_perms,
+            })
+            .map_err(|_| Error::with_message(Errno::EFAULT, "the page is not accessible"))?;
+
+            let mut cursor = self.vm_space.cursor(&(page_addr..page_addr + PAGE_SIZE))?;
+            if let VmItem::Mapped { frame, prop, .. } = cursor.query()? {
+                if VmPerms::from(prop.flags).contains(required_perms) {
+                    return Ok(frame);
+                }
+            }
+        }
+    }
+
+    /// Locks the pages in the range.
+    ///
+    /// If `is_all` is true, the range is the whole VMAR, which does not have to be fully
+    /// mapped, and the failures to populate the pages are ignored like Linux.
+    fn lock(&self, range: Range<Vaddr>, option: LockOption, is_all: bool) -> Result<()> {
+        {
+            let mut inner = self.inner.write();
+            if !is_all {
+                inner.check_fully_mapped(&range)?;
+            }
+            inner.check_lock_size(inner.count_unlocked_size(&range))?;
+            inner.set_locked(&range, true)?;
+        }
+
+        if option == LockOption::Populate {
+            let res = self.populate(&range);
+            if !is_all {
+                res.map_err(|_| Error::with_message(Errno::ENOMEM, "cannot populate the pages"))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Populates the pages of the mappings in the range, as if they were accessed.
+    fn populate(&self, range: &Range<Vaddr>) -> Result<()> {
+        let ranges_to_populate: Vec<_> = self
+            .inner
+            .read()
+            .vm_mappings
+            .find(range)
+            .filter_map(|vm_mapping| {
+                let perms = vm_mapping.populate_perms()?;
+                Some((get_intersected_range(range, &vm_mapping.range()), perms))
+            })
+            .collect();
+
+        for (range, required_perms) in ranges_to_populate {
+            for address in range.step_by(PAGE_SIZE) {
+                self.handle_page_fault(&PageFaultInfo {
+                    address,
+                    required_perms,
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    fn page_residency(&self, range: Range<Vaddr>) -> Result<Vec<bool>> {
+        let inner = self.inner.read();
+        inner.check_fully_mapped(&range)?;
+
+        let mut residency = vec![false; range.len() / PAGE_SIZE];
+        for item in self.vm_space.cursor(&range)? {
+            if let VmItem::Mapped { va, .. } = item {
+                residency[(va - range.start) / PAGE_SIZE] = true;
+            }
+        }
+        Ok(residency)
+    }
+
+    fn page_status(&self, range: Range<Vaddr>) -> Result<Vec<Option<PageStatus>>> {
+        let inner = self.inner.read();
+
+        let mut status = vec![None; range.len() / PAGE_SIZE];
+        for vm_mapping in inner.vm_mappings.find(&range) {
+            let mapping_range =
+                vm_mapping.map_to_addr().max(range.start)..vm_mapping.map_end().min(range.end);
+            for item in self.vm_space.cursor(&mapping_range)? {
+                let VmItem::Mapped { va, frame, .. } = item else {
+                    continue;
+                };
+                // Besides the frame handle here, a page is referenced by each of its mappings
+                // and by the VMO that it belongs to. The private copies of the pages of a VMO
+                // (e.g., after copy-on-write) are assumed not to be shared by other mappings.
+                let nr_refs = frame.reference_count() as usize - 1;
+                let is_vmo_page = vm_mapping.vmo().is_some() && nr_refs > 1;
+                let nr_mappings = if is_vmo_page { nr_refs - 1 } else { nr_refs };
+                status[(va - range.start) / PAGE_SIZE] = Some(PageStatus {
+                    paddr: frame.start_paddr(),
+                    is_vmo_page,
+                    is_exclusive: nr_mappings == 1,
+                });
+            }
+        }
+        Ok(status)
+    }
+
     /// Clears all content of the root VMAR.
     fn clear_root_vmar(&self) -> Result<()> {
         self.vm_space.clear().unwrap();
         let mut inner = self.inner.write();
         inner.vm_mappings.clear();
+        TOTAL_LOCKED_VM.fetch_sub(inner.locked_vm, Ordering::Relaxed);
+        inner.locked_vm = 0;
+        inner.lock_future = None;
         Ok(())
     }
 
@@ -433,6 +898,8 @@ impl Vmar_ {
         {
             let inner = self.inner.read();
             let mut new_inner = new_vmar_.inner.write();
+            new_inner.mmap_base = inner.mmap_base;
+            new_inner.mmap_top = inner.mmap_top;
 
             // Clone mappings.
             let new_vmspace = new_vmar_.vm_space();
@@ -460,6 +927,17 @@ impl Vmar_ {
             cur_cursor.flusher().sync_tlb_flush();
         }
 
+        // The mergeable mappings are inherited, so the new VMAR is scanned as well.
+        if self
+            .inner
+            .read()
+            .vm_mappings
+            .iter()
+            .any(|vm_mapping| vm_mapping.is_mergeable())
+        {
+            ksm::register(&new_vmar_);
+        }
+
         Ok(new_vmar_)
     }
 }
@@ -495,6 +973,8 @@ pub struct VmarMapOptions<'a, R1, R2> {
     is_shared: bool,
     // Whether the mapping needs to handle surrounding pages when handling page fault.
     handle_page_faults_around: bool,
+    // How the mapping is locked, if it is mapped with `MAP_LOCKED`
+    lock: Option<LockOption>,
 }
 
 impl<'a, R1, R2> VmarMapOptions<'a, R1, R2> {
@@ -517,6 +997,7 @@ impl<'a, R1, R2> VmarMapOptions<'a, R1, R2> {
             can_overwrite: false,
             is_shared: false,
             handle_page_faults_around: false,
+            lock: None,
         }
     }
 
@@ -609,6 +1090,15 @@ impl<'a, R1, R2> VmarMapOptions<'a, R1, R2> {
         self
     }
 
+    /// Sets the mapping to be locked.
+    ///
+    /// The mapping is also locked if all the future mappings of the parent VMAR are locked
+    /// (see [`Vmar::set_lock_future`]).
+    pub fn lock(mut self, option: LockOption) -> Self {
+        self.lock = Some(option);
+        self
+    }
+
     /// Creates the mapping and adds it to the parent VMAR.
     ///
     /// All options will be checked at this point.
@@ -628,10 +1118,23 @@ impl<'a, R1, R2> VmarMapOptions<'a, R1, R2> {
             can_overwrite,
             is_shared,
             handle_page_faults_around,
+            lock,
         } = self;
 
         let mut inner = parent.0.inner.write();
 
+        let lock = lock.or(inner.lock_future);
+        if lock.is_some() {
+            inner
+                .check_lock_size(map_size)
+                .map_err(|err| match err.error() {
+                    Errno::ENOMEM => {
+                        Error::with_message(Errno::EAGAIN, "locked memory limit overflow")
+                    }
+                    _ => err,
+                })?;
+        }
+
         inner.check_expand_size(map_size).or_else(|e| {
             if can_overwrite {
                 let offset = offset.ok_or(Error::with_message(
@@ -647,7 +1150,10 @@ impl<'a, R1, R2> VmarMapOptions<'a, R1, R2> {
         })?;
 
         // Allocates a free region.
-        trace!("allocate free region, map_size = 0x{:x}, offset = {:x?}, align = 0x{:x}, can_overwrite = {}", map_size, offset, align, can_overwrite);
+        trace!(
+            "allocate free region, map_size = 0x{:x}, offset = {:x?}, align = 0x{:x}, can_overwrite = {}",
+            map_size, offset, align, can_overwrite
+        );
         let map_to_addr = if can_overwrite {
             // If can overwrite, the offset is ensured not to be `None`.
             let offset = offset.ok_or(Error::with_message(
@@ -673,10 +1179,17 @@ impl<'a, R1, R2> VmarMapOptions<'a, R1, R2> {
             is_shared,
             handle_page_faults_around,
             perms,
+            lock.is_some(),
         );
 
         // Add the mapping to the VMAR.
         inner.insert(vm_mapping);
+        drop(inner);
+
+        if lock == Some(LockOption::Populate) {
+            // Like Linux, the failures to populate the pages are ignored.
+            let _ = parent.0.populate(&(map_to_addr..map_to_addr + map_size));
+        }
 
         Ok(map_to_addr)
     }
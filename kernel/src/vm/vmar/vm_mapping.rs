@@ -16,7 +16,11 @@ use super::interval_set::Interval;
 use crate::{
     prelude::*,
     thread::exception::PageFaultInfo,
-    vm::{perms::VmPerms, util::duplicate_frame, vmo::Vmo},
+    vm::{
+        perms::VmPerms,
+        util::{duplicate_frame, is_zero_page, new_frame_for_cow, zero_page},
+        vmo::Vmo,
+    },
 };
 
 /// Mapping a range of physical pages into a `Vmar`.
@@ -62,6 +66,14 @@ pub(super) struct VmMapping {
     ///
     /// All pages within the same `VmMapping` have the same permissions.
     perms: VmPerms,
+    /// Whether the pages in the mapping are locked (e.g., by `mlock`).
+    ///
+    /// The locked pages are accounted against `RLIMIT_MEMLOCK`.
+    is_locked: bool,
+    /// Whether the pages in the mapping can be merged by KSM (i.e., `MADV_MERGEABLE`).
+    ///
+    /// Only the anonymous private mappings can be mergeable.
+    is_mergeable: bool,
 }
 
 impl Interval<Vaddr> for VmMapping {
@@ -80,6 +92,7 @@ impl VmMapping {
         is_shared: bool,
         handle_page_faults_around: bool,
         perms: VmPerms,
+        is_locked: bool,
     ) -> Self {
         Self {
             map_size,
@@ -88,12 +101,16 @@ impl VmMapping {
             is_shared,
             handle_page_faults_around,
             perms,
+            is_locked,
+            is_mergeable: false,
         }
     }
 
     pub(super) fn new_fork(&self) -> Result<VmMapping> {
         Ok(VmMapping {
             vmo: self.vmo.as_ref().map(|vmo| vmo.dup()).transpose()?,
+            // The memory locks are not inherited by the child.
+            is_locked: false,
             ..*self
         })
     }
@@ -117,6 +134,26 @@ impl VmMapping {
     pub fn perms(&self) -> VmPerms {
         self.perms
     }
+
+    /// Returns whether the mapping is shared.
+    pub fn is_shared(&self) -> bool {
+        self.is_shared
+    }
+
+    /// Returns whether the pages in the mapping are locked.
+    pub fn is_locked(&self) -> bool {
+        self.is_locked
+    }
+
+    /// Returns whether the pages in the mapping can be merged by KSM.
+    pub fn is_mergeable(&self) -> bool {
+        self.is_mergeable
+    }
+
+    /// Returns the mapped VMO, if the mapping is VMO-backed.
+    pub(super) fn vmo(&self) -> Option<&MappedVmo> {
+        self.vmo.as_ref()
+    }
 }
 
 /****************************** Page faults **********************************/
@@ -174,8 +211,9 @@ impl VmMapping {
                 // the fork without accessing it, we are the only reference to the
                 // frame. We can directly map the frame as writable without
                 // copying. In this case, the reference count of the frame is 2 (
-                // one for the mapping and one for the frame handle itself).
-                let only_reference = frame.reference_count() == 2;
+                // one for the mapping and one for the frame handle itself). The zero
+                // page is never written even if it is only referenced here.
+                let only_reference = frame.reference_count() == 2 && !is_zero_page(&frame);
 
                 let new_flags = PageFlags::W | PageFlags::ACCESSED | PageFlags::DIRTY;
 
@@ -184,7 +222,7 @@ impl VmMapping {
                     cursor.flusher().issue_tlb_flush(TlbFlushOp::Address(va));
                     cursor.flusher().dispatch_tlb_flush();
                 } else {
-                    let new_frame = duplicate_frame(&frame)?;
+                    let new_frame = new_frame_for_cow(&frame)?;
                     prop.flags |= new_flags;
                     cursor.map(new_frame.into(), prop);
                 }
@@ -220,6 +258,11 @@ impl VmMapping {
     fn prepare_page(&self, page_fault_addr: Vaddr, write: bool) -> Result<(UFrame, bool)> {
         let mut is_readonly = false;
         let Some(vmo) = &self.vmo else {
+            if !write {
+                // Read access to untouched anonymous memory. The zero page is mapped
+                // read-only, and is replaced by a new frame on the first write.
+                return Ok((zero_page().clone(), true));
+            }
             return Ok((FrameAllocOptions::new().alloc_frame()?.into(), is_readonly));
         };
 
@@ -398,7 +441,15 @@ impl VmMapping {
 
         let mut cursor = vm_space.cursor_mut(&range).unwrap();
 
-        let op = |p: &mut PageProperty| p.flags = perms.into();
+        // The pages that are not writable may be shared for COW (e.g., the pages after
+        // `fork` and the zero page), so they are left for the page faults to make writable.
+        let op = |p: &mut PageProperty| {
+            let was_writable = p.flags.contains(PageFlags::W);
+            p.flags = perms.into();
+            if !was_writable {
+                p.flags -= PageFlags::W;
+            }
+        };
         while cursor.virt_addr() < range.end {
             if let Some(va) = cursor.protect_next(range.end - cursor.virt_addr(), op) {
                 cursor.flusher().issue_tlb_flush(TlbFlushOp::Range(va));
@@ -411,6 +462,44 @@ impl VmMapping {
 
         Self { perms, ..self }
     }
+
+    /// Locks or unlocks the pages of the mapping.
+    ///
+    /// The pages are not populated by this method.
+    pub(super) fn set_locked(self, is_locked: bool) -> Self {
+        Self { is_locked, ..self }
+    }
+
+    /// Returns whether the mapping can be made mergeable, i.e., an anonymous private mapping.
+    pub(super) fn can_be_mergeable(&self) -> bool {
+        self.vmo.is_none() && !self.is_shared
+    }
+
+    /// Sets whether the pages of the mapping can be merged by KSM.
+    ///
+    /// The pages that are merged are not broken out by this method.
+    pub(super) fn set_mergeable(self, is_mergeable: bool) -> Self {
+        debug_assert!(!is_mergeable || self.can_be_mergeable());
+        Self {
+            is_mergeable,
+            ..self
+        }
+    }
+
+    /// Returns the permissions to populate the pages of the mapping with, or `None` if the
+    /// pages cannot be populated.
+    ///
+    /// The pages of a private writable mapping are populated with the write permission so
+    /// that the COW is done in advance.
+    pub(super) fn populate_perms(&self) -> Option<VmPerms> {
+        if self.perms.is_empty() {
+            None
+        } else if !self.is_shared && self.perms.contains(VmPerms::WRITE) {
+            Some(VmPerms::WRITE)
+        } else {
+            Some(VmPerms::empty())
+        }
+    }
 }
 
 /// A wrapper that represents a mapped [`Vmo`] and provide required functionalities
@@ -432,6 +521,16 @@ impl MappedVmo {
         self.range.len()
     }
 
+    /// Returns the underlying VMO.
+    pub(super) fn vmo(&self) -> &Vmo {
+        &self.vmo
+    }
+
+    /// Returns the offset in the VMO where the mapping starts.
+    pub(super) fn offset(&self) -> usize {
+        self.range.start
+    }
+
     /// Gets the committed frame at the input offset in the mapped VMO.
     ///
     /// If the VMO has not committed a frame at this index, it will commit
@@ -447,6 +447,13 @@ impl<R> Vmo<R> {
     pub fn flags(&self) -> VmoFlags {
         self.0.flags()
     }
+
+    /// Returns the number of the capabilities that refer to the VMO.
+    ///
+    /// Note that each VMO-backed mapping holds a capability of the VMO.
+    pub fn capability_count(&self) -> usize {
+        Arc::strong_count(&self.0)
+    }
 }
 
 /// Gets the page index range that contains the offset range of VMO.
@@ -0,0 +1,371 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Embeds the symbol table into the kernel ELF.
+//!
+//! The kernel reserves the `.kallsyms` section, which is filled here with the functions and
+//! the objects in the ELF symbol table after the kernel is linked. Filling the reserved
+//! section does not move any code or data, so the kernel needs not be linked again. The
+//! format of the table is documented in `ostd::kallsyms`, which should be kept in sync.
+
+use std::{fs, path::Path};
+
+use crate::warn_msg;
+
+const MAGIC: &[u8; 4] = b"KSYM";
+const MARKER_INTERVAL: usize = 64;
+const MAX_NAME_LEN: usize = 255;
+
+const SHT_NOBITS: u32 = 8;
+const SHT_SYMTAB: u32 = 2;
+const SHF_WRITE: u64 = 0x1;
+const SHF_ALLOC: u64 = 0x2;
+const SHF_EXECINSTR: u64 = 0x4;
+const SHN_LORESERVE: u16 = 0xff00;
+const STB_LOCAL: u8 = 0;
+const STT_NOTYPE: u8 = 0;
+const STT_OBJECT: u8 = 1;
+const STT_FUNC: u8 = 2;
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Symbol {
+    addr: u64,
+    name: String,
+    size: u64,
+    kind: u8,
+}
+
+struct Section {
+    name: u32,
+    typ: u32,
+    flags: u64,
+    offset: usize,
+    size: usize,
+    link: u32,
+}
+
+/// Fills the `.kallsyms` section of the kernel ELF with its symbol table.
+///
+/// The kernel ELF is not written if the table is unchanged.
+pub fn embed_kallsyms(elf_path: impl AsRef<Path>) {
+    let elf_path = elf_path.as_ref();
+    let mut image = fs::read(elf_path).unwrap();
+    let Some(sections) = parse_sections(&image) else {
+        warn_msg!("The kernel is not a valid ELF64 file; no symbol table is embedded");
+        return;
+    };
+    let shstrndx = read_u16(&image, 62).unwrap_or(0) as usize;
+    let section_name = |section: &Section| {
+        let shstrtab = sections.get(shstrndx)?;
+        read_str(&image, shstrtab.offset + section.name as usize)
+    };
+    let Some(area) = sections
+        .iter()
+        .find(|section| section_name(section) == Some(".kallsyms") && section.typ != SHT_NOBITS)
+        .map(|section| section.offset..section.offset + section.size)
+    else {
+        // The kernel is not based on OSTD, or is built with an old version of OSTD.
+        return;
+    };
+
+    let mut symbols = read_symbols(&image, &sections).unwrap_or_default();
+    let mut table = encode(&symbols);
+    if table.len() > area.len() {
+        warn_msg!(
+            "The symbol table ({} bytes) does not fit in the kernel ({} bytes); only the functions are embedded",
+            table.len(),
+            area.len()
+        );
+        symbols.retain(|symbol| symbol.kind.eq_ignore_ascii_case(&b't'));
+        table = encode(&symbols);
+    }
+    if table.len() > area.len() {
+        warn_msg!("The function table does not fit in the kernel; no symbol table is embedded");
+        table = encode(&[]);
+    }
+    table.resize(area.len(), 0);
+
+    if image[area.clone()] != table[..] {
+        image[area].copy_from_slice(&table);
+        fs::write(elf_path, image).unwrap();
+    }
+}
+
+fn parse_sections(image: &[u8]) -> Option<Vec<Section>> {
+    // A little-endian ELF64 file.
+    if image.get(..6)? != b"\x7fELF\x02\x01" {
+        return None;
+    }
+    let shoff = read_u64(image, 40)? as usize;
+    let shnum = read_u16(image, 60)? as usize;
+    (0..shnum)
+        .map(|index| {
+            let base = shoff + index * 64;
+            let section = Section {
+                name: read_u32(image, base)?,
+                typ: read_u32(image, base + 4)?,
+                flags: read_u64(image, base + 8)?,
+                offset: read_u64(image, base + 24)? as usize,
+                size: read_u64(image, base + 32)? as usize,
+                link: read_u32(image, base + 40)?,
+            };
+            if section.typ != SHT_NOBITS {
+                image.get(section.offset..section.offset + section.size)?;
+            }
+            Some(section)
+        })
+        .collect()
+}
+
+/// Reads the functions and the objects (and the untyped labels in the code) with their
+/// names demangled.
+fn read_symbols(image: &[u8], sections: &[Section]) -> Option<Vec<Symbol>> {
+    let symtab = sections.iter().find(|section| section.typ == SHT_SYMTAB)?;
+    let strtab = sections.get(symtab.link as usize)?;
+
+    let mut symbols = Vec::new();
+    for base in (symtab.offset..symtab.offset + symtab.size).step_by(24) {
+        let info = *image.get(base + 4)?;
+        let shndx = read_u16(image, base + 6)?;
+        let (bind, typ) = (info >> 4, info & 0xf);
+        if shndx == 0 || shndx >= SHN_LORESERVE {
+            continue;
+        }
+        let section = sections.get(shndx as usize)?;
+        let is_code = section.flags & SHF_EXECINSTR != 0;
+        if section.flags & SHF_ALLOC == 0
+            || !(typ == STT_FUNC || typ == STT_OBJECT || (typ == STT_NOTYPE && is_code))
+        {
+            continue;
+        }
+        let name = read_str(image, strtab.offset + read_u32(image, base)? as usize)?;
+        // The local labels and the mapping symbols (e.g., `$x` on RISC-V) are not interesting.
+        if name.is_empty() || name.starts_with(".L") || name.starts_with('$') {
+            continue;
+        }
+
+        let kind = if is_code {
+            b't'
+        } else if section.typ == SHT_NOBITS {
+            b'b'
+        } else if section.flags & SHF_WRITE != 0 {
+            b'd'
+        } else {
+            b'r'
+        };
+        symbols.push(Symbol {
+            addr: read_u64(image, base + 8)?,
+            name: truncate(demangle(name)),
+            size: read_u64(image, base + 16)?,
+            kind: if bind == STB_LOCAL {
+                kind
+            } else {
+                kind.to_ascii_uppercase()
+            },
+        });
+    }
+    symbols.sort();
+    symbols.dedup();
+    Some(symbols)
+}
+
+/// Encodes the symbols, which are sorted by their addresses.
+fn encode(symbols: &[Symbol]) -> Vec<u8> {
+    let base = symbols.first().map_or(0, |symbol| symbol.addr);
+    // The offsets must fit in `u32`, which is true unless the symbols are not in the kernel.
+    let symbols: Vec<_> = symbols
+        .iter()
+        .filter(|symbol| symbol.addr - base <= u32::MAX as u64)
+        .collect();
+
+    let mut offsets = Vec::new();
+    let mut sizes = Vec::new();
+    let mut markers = Vec::new();
+    let mut names = Vec::new();
+    let mut last_name = "";
+    for (index, symbol) in symbols.iter().enumerate() {
+        offsets.extend_from_slice(&((symbol.addr - base) as u32).to_le_bytes());
+        sizes.extend_from_slice(&(symbol.size.min(u32::MAX as u64) as u32).to_le_bytes());
+        if index % MARKER_INTERVAL == 0 {
+            markers.extend_from_slice(&(names.len() as u32).to_le_bytes());
+            last_name = "";
+        }
+        let prefix_len = common_prefix_len(last_name, &symbol.name);
+        let rest = &symbol.name.as_bytes()[prefix_len..];
+        names.extend_from_slice(&[symbol.kind, prefix_len as u8, rest.len() as u8]);
+        names.extend_from_slice(rest);
+        last_name = &symbol.name;
+    }
+
+    let mut table = Vec::new();
+    table.extend_from_slice(MAGIC);
+    table.extend_from_slice(&(symbols.len() as u32).to_le_bytes());
+    table.extend_from_slice(&base.to_le_bytes());
+    table.extend_from_slice(&offsets);
+    table.extend_from_slice(&sizes);
+    table.extend_from_slice(&markers);
+    table.extend_from_slice(&names);
+    table
+}
+
+fn common_prefix_len(a: &str, b: &str) -> usize {
+    a.bytes().zip(b.bytes()).take_while(|(a, b)| a == b).count()
+}
+
+fn truncate(mut name: String) -> String {
+    let mut len = name.len().min(MAX_NAME_LEN);
+    while !name.is_char_boundary(len) {
+        len -= 1;
+    }
+    name.truncate(len);
+    name
+}
+
+/// Demangles a symbol name of the legacy Rust mangling, without the hash.
+///
+/// The other names are returned as they are.
+fn demangle(name: &str) -> String {
+    // The suffixes added by LLVM (e.g., `.llvm.1234`) are removed.
+    let base_name = name
+        .split_once(".llvm.")
+        .map_or(name, |(base_name, _)| base_name);
+    let Some(mut rest) = base_name
+        .strip_prefix("_ZN")
+        .and_then(|rest| rest.strip_suffix('E'))
+    else {
+        return name.to_string();
+    };
+
+    let mut idents = Vec::new();
+    while !rest.is_empty() {
+        let nr_digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+        let Some(len) = rest[..nr_digits].parse::<usize>().ok() else {
+            return name.to_string();
+        };
+        let Some(ident) = rest.get(nr_digits..nr_digits + len) else {
+            return name.to_string();
+        };
+        idents.push(ident);
+        rest = &rest[nr_digits + len..];
+    }
+    if let Some(hash) = idents.last() {
+        if hash.len() == 17
+            && hash.starts_with('h')
+            && hash[1..].bytes().all(|b| b.is_ascii_hexdigit())
+        {
+            idents.pop();
+        }
+    }
+
+    idents
+        .into_iter()
+        .map(unescape)
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
+/// Unescapes an identifier of the legacy Rust mangling.
+fn unescape(ident: &str) -> String {
+    // A leading `_` is added if the identifier starts with `$`.
+    let mut rest = ident.strip_prefix("_$").map_or(ident, |_| &ident[1..]);
+    let mut output = String::new();
+    while let Some(c) = rest.chars().next() {
+        if let Some(after) = rest.strip_prefix("..") {
+            output.push_str("::");
+            rest = after;
+            continue;
+        }
+        if c == '$' {
+            if let Some((escape, after)) = rest[1..].split_once('$') {
+                let unescaped = match escape {
+                    "SP" => Some('@'),
+                    "BP" => Some('*'),
+                    "RF" => Some('&'),
+                    "LT" => Some('<'),
+                    "GT" => Some('>'),
+                    "LP" => Some('('),
+                    "RP" => Some(')'),
+                    "C" => Some(','),
+                    _ => escape
+                        .strip_prefix('u')
+                        .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                        .and_then(char::from_u32),
+                };
+                if let Some(unescaped) = unescaped {
+                    output.push(unescaped);
+                    rest = after;
+                    continue;
+                }
+            }
+        }
+        output.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    output
+}
+
+fn read_u16(image: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        image.get(offset..offset + 2)?.try_into().unwrap(),
+    ))
+}
+
+fn read_u32(image: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        image.get(offset..offset + 4)?.try_into().unwrap(),
+    ))
+}
+
+fn read_u64(image: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(
+        image.get(offset..offset + 8)?.try_into().unwrap(),
+    ))
+}
+
+fn read_str(image: &[u8], offset: usize) -> Option<&str> {
+    let bytes = image.get(offset..)?;
+    let len = bytes.iter().position(|b| *b == 0)?;
+    std::str::from_utf8(&bytes[..len]).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_demangle() {
+        assert_eq!(
+            demangle("_ZN4ostd2mm14heap_allocator10HEAP_SPACE17h85a5340e6564f69dE.llvm.15305379556759765072"),
+            "ostd::mm::heap_allocator::HEAP_SPACE"
+        );
+        assert_eq!(
+            demangle("_ZN68_$LT$alloc..boxed..Box$LT$T$GT$$u20$as$u20$core..ops..drop..Drop$GT$4drop17h0123456789abcdefE"),
+            "<alloc::boxed::Box<T> as core::ops::drop::Drop>::drop"
+        );
+        assert_eq!(demangle("__from_kernel"), "__from_kernel");
+        assert_eq!(demangle("_ZN3foo"), "_ZN3foo");
+    }
+
+    #[test]
+    fn test_encode() {
+        let symbol = |addr, name: &str| Symbol {
+            addr,
+            name: name.to_string(),
+            size: 0x10,
+            kind: b'T',
+        };
+        let symbols = [symbol(0x1000, "ostd::mm::a"), symbol(0x1010, "ostd::mm::b")];
+        let table = encode(&symbols);
+
+        assert_eq!(&table[0..4], MAGIC);
+        assert_eq!(u32::from_le_bytes(table[4..8].try_into().unwrap()), 2);
+        assert_eq!(u64::from_le_bytes(table[8..16].try_into().unwrap()), 0x1000);
+        // The offsets, the sizes and the marker.
+        assert_eq!(&table[16..20], &[0, 0, 0, 0]);
+        assert_eq!(&table[20..24], &[0x10, 0, 0, 0]);
+        assert_eq!(&table[24..32], &[0x10, 0, 0, 0, 0x10, 0, 0, 0]);
+        assert_eq!(&table[32..36], &[0, 0, 0, 0]);
+        // The second name shares the prefix `ostd::mm::` with the first name.
+        assert_eq!(&table[36..50], b"T\x00\x0bostd::mm::a");
+        assert_eq!(&table[50..], b"T\x0a\x01b");
+    }
+}
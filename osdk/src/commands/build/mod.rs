@@ -2,6 +2,7 @@
 
 mod bin;
 mod grub;
+mod kallsyms;
 mod qcow2;
 
 use std::{
@@ -144,6 +145,7 @@ pub fn do_cached_build(
         &cargo_target_directory,
         rustflags,
     );
+    kallsyms::embed_kallsyms(aster_elf.path());
 
     // Check the existing bundle's reusability
     if let Some(existing_bundle) = get_reusable_existing_bundle(&bundle_path, config, action) {
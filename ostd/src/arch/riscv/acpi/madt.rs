@@ -0,0 +1,71 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The Multiple APIC Description Table (MADT) of RISC-V.
+//!
+//! On RISC-V, the MADT describes each hart with a RISC-V Interrupt Controller (RINTC) structure,
+//! and the external interrupt controllers (e.g., the PLIC) with their own structures. The RINTC of
+//! a hart refers to the context of the hart in its external interrupt controller.
+//!
+//! Reference: <https://uefi.org/specs/ACPI/6.5/05_ACPI_Software_Programming_Model.html#multiple-apic-description-table-madt>
+
+use super::{find_table, read_u16, read_u32, read_u64, structures};
+use crate::mm::Paddr;
+
+/// The offset of the interrupt controller structures.
+const STRUCTURES_OFFSET: usize = 44;
+
+const TYPE_RINTC: u16 = 0x18;
+const TYPE_PLIC: u16 = 0x1B;
+
+/// A hart described by its RINTC.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Hart {
+    pub(crate) hart_id: usize,
+    /// The ACPI processor UID, which identifies the hart in the other tables (e.g., the RHCT)
+    pub(crate) uid: u32,
+    /// The ID of the external interrupt controller of the hart.
+    ///
+    /// For the PLIC, bits 24-31 are the PLIC ID and bits 0-15 are the supervisor-mode context of
+    /// the hart.
+    pub(crate) ext_intc_id: u32,
+}
+
+/// A PLIC described by the MADT.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Plic {
+    pub(crate) id: u8,
+    /// The number of the interrupt sources
+    pub(crate) nr_sources: usize,
+    pub(crate) base: Paddr,
+    pub(crate) size: usize,
+}
+
+fn madt_structures() -> impl Iterator<Item = (u16, &'static [u8])> {
+    find_table(b"APIC")
+        .into_iter()
+        .flat_map(|madt| structures(madt, STRUCTURES_OFFSET, false))
+}
+
+/// Returns the harts in the MADT.
+pub(crate) fn harts() -> impl Iterator<Item = Hart> {
+    madt_structures()
+        .filter(|(type_, _)| *type_ == TYPE_RINTC)
+        .filter_map(|(_, rintc)| {
+            Some(Hart {
+                hart_id: read_u64(rintc, 8)? as usize,
+                uid: read_u32(rintc, 16)?,
+                ext_intc_id: read_u32(rintc, 20)?,
+            })
+        })
+}
+
+/// Returns the first PLIC in the MADT.
+pub(crate) fn plic() -> Option<Plic> {
+    let (_, plic) = madt_structures().find(|(type_, _)| *type_ == TYPE_PLIC)?;
+    Some(Plic {
+        id: *plic.get(3)?,
+        nr_sources: read_u16(plic, 12)? as usize,
+        size: read_u32(plic, 20)? as usize,
+        base: read_u64(plic, 24)? as Paddr,
+    })
+}
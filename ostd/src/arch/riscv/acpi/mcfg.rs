@@ -0,0 +1,41 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The PCI Express memory-mapped configuration space base address description table (MCFG).
+//!
+//! Reference: <https://wiki.osdev.org/PCI_Express>
+
+use core::ops::RangeInclusive;
+
+use super::{find_table, read_u16, read_u64};
+use crate::mm::Paddr;
+
+/// The offset of the entries, which is after the header and 8 reserved bytes.
+const ENTRIES_OFFSET: usize = 44;
+const ENTRY_SIZE: usize = 16;
+
+/// The ECAM region of a PCI segment group.
+#[derive(Debug, Clone)]
+pub(crate) struct EcamRegion {
+    /// The base address of the ECAM region, which corresponds to the bus zero.
+    pub(crate) base: Paddr,
+    pub(crate) segment: u16,
+    pub(crate) buses: RangeInclusive<u8>,
+}
+
+/// Returns the ECAM regions in the MCFG.
+pub(crate) fn ecam_regions() -> impl Iterator<Item = EcamRegion> {
+    find_table(b"MCFG")
+        .into_iter()
+        .flat_map(|mcfg| {
+            mcfg.get(ENTRIES_OFFSET..)
+                .unwrap_or_default()
+                .chunks_exact(ENTRY_SIZE)
+        })
+        .filter_map(|entry| {
+            Some(EcamRegion {
+                base: read_u64(entry, 0)? as Paddr,
+                segment: read_u16(entry, 8)?,
+                buses: entry[10]..=entry[11],
+            })
+        })
+}
@@ -0,0 +1,293 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The ACPI tables of the RISC-V server platforms.
+//!
+//! The server platforms (e.g., those following the RISC-V server platform specification) describe
+//! the harts and the devices with ACPI instead of a device tree. Their firmware still passes a
+//! device tree, which only has the `/chosen` node for the kernel command line and the UEFI system
+//! table, as the EFI stub of Linux does.
+//!
+//! The RSDP is found by `acpi_rsdp=ADDRESS` in the kernel command line or by the ACPI 2.0 entry of
+//! the configuration table of UEFI. ACPI is used if the RSDP is found and
+//!  - `acpi=on` or `acpi=force` is in the kernel command line; or
+//!  - the device tree does not describe the harts (i.e., there is no `/cpus` node), and `acpi=off`
+//!    is not in the kernel command line.
+//!
+//! Otherwise, the device tree is used as before. When ACPI is used, the following tables replace
+//! the corresponding parts of the device tree:
+//!  - RHCT for the ISA strings, the cache block sizes and the time base frequency ([`rhct`]);
+//!  - MADT for the harts and the PLIC ([`madt`]);
+//!  - MCFG for the ECAM regions of the PCIe buses ([`mcfg`]).
+//!
+//! Reference: <https://uefi.org/specs/ACPI/6.5/05_ACPI_Software_Programming_Model.html>
+
+pub(crate) mod madt;
+pub(crate) mod mcfg;
+pub(crate) mod rhct;
+
+use core::ops::Range;
+
+use align_ext::AlignExt;
+use spin::Once;
+
+use crate::{
+    arch::boot::DEVICE_TREE,
+    mm::{paddr_to_vaddr, Paddr, PAGE_SIZE},
+};
+
+/// The size of the header of the system description tables.
+const HEADER_SIZE: usize = 36;
+
+/// The maximum number of the tables that are listed in the XSDT (or the RSDT).
+const MAX_NR_TABLES: usize = 64;
+
+/// The GUID of the ACPI 2.0 entry of the UEFI configuration table, in the memory layout.
+const ACPI_20_TABLE_GUID: [u8; 16] = [
+    0x71, 0xe8, 0x68, 0x88, 0xf1, 0xe4, 0xd3, 0x11, 0xbc, 0x22, 0x00, 0x80, 0xc7, 0x3c, 0x88, 0x81,
+];
+
+struct AcpiTables {
+    rsdp: Paddr,
+    /// The root table, which is the XSDT (or the RSDT of ACPI 1.0)
+    root: Paddr,
+    tables: [Paddr; MAX_NR_TABLES],
+    nr_tables: usize,
+}
+
+static ACPI_TABLES: Once<AcpiTables> = Once::new();
+
+/// Finds the ACPI tables and decides whether they are used instead of the device tree.
+///
+/// This should be called in the boot context after the device tree is parsed and before the
+/// memory regions are parsed, since the tables should be reserved (see [`table_ranges`]).
+pub(in crate::arch) fn init(cmdline: &str) {
+    let mut force = false;
+    for arg in cmdline.split(' ') {
+        match arg {
+            "acpi=off" => return,
+            "acpi=on" | "acpi=force" => force = true,
+            _ => {}
+        }
+    }
+    let is_dt_empty = DEVICE_TREE.get().unwrap().find_node("/cpus").is_none();
+    if !force && !is_dt_empty {
+        return;
+    }
+
+    let Some(rsdp) = find_rsdp(cmdline) else {
+        if force {
+            crate::early_println!("[ACPI]: RSDP not found, using the device tree");
+        }
+        return;
+    };
+    let Some(tables) = parse_root_table(rsdp) else {
+        crate::early_println!("[ACPI]: Invalid RSDP at {:#x}, using the device tree", rsdp);
+        return;
+    };
+    ACPI_TABLES.call_once(|| tables);
+}
+
+/// Returns whether the ACPI tables are used instead of the device tree.
+pub(crate) fn is_enabled() -> bool {
+    ACPI_TABLES.is_completed()
+}
+
+/// Returns the physical address of the RSDP, if ACPI is used.
+pub(crate) fn rsdp() -> Option<Paddr> {
+    ACPI_TABLES.get().map(|tables| tables.rsdp)
+}
+
+/// Returns the page-aligned physical memory ranges of the RSDP and the tables.
+///
+/// The ranges should be reserved, since the tables are read after the memory allocators are
+/// initialized.
+pub(in crate::arch) fn table_ranges() -> impl Iterator<Item = Range<Paddr>> {
+    let tables = ACPI_TABLES.get();
+    let rsdp = tables.map(|tables| tables.rsdp..tables.rsdp + RSDP_V2_SIZE);
+    let root = tables.and_then(|tables| Some(tables.root..tables.root + table_len(tables.root)?));
+    let others = tables
+        .into_iter()
+        .flat_map(|tables| tables.tables[..tables.nr_tables].iter())
+        .filter_map(|table| Some(*table..*table + table_len(*table)?));
+    rsdp.into_iter()
+        .chain(root)
+        .chain(others)
+        .map(|range| range.start.align_down(PAGE_SIZE)..range.end.align_up(PAGE_SIZE))
+}
+
+/// Finds the first table with the signature, whose checksum is valid.
+///
+/// The returned bytes include the header of the table.
+pub(crate) fn find_table(signature: &[u8; 4]) -> Option<&'static [u8]> {
+    let tables = ACPI_TABLES.get()?;
+    tables.tables[..tables.nr_tables]
+        .iter()
+        .filter_map(|table| table_bytes(*table))
+        .find(|bytes| &bytes[0..4] == signature)
+}
+
+const RSDP_V1_SIZE: usize = 20;
+const RSDP_V2_SIZE: usize = 36;
+
+fn find_rsdp(cmdline: &str) -> Option<Paddr> {
+    if let Some(arg) = cmdline
+        .split(' ')
+        .find_map(|arg| arg.strip_prefix("acpi_rsdp="))
+    {
+        return parse_addr(arg);
+    }
+
+    // The EFI stub passes the physical address of the UEFI system table.
+    let system_table = DEVICE_TREE
+        .get()
+        .unwrap()
+        .find_node("/chosen")?
+        .property("linux,uefi-system-table")?
+        .value;
+    let system_table = u64::from_be_bytes(system_table.get(0..8)?.try_into().unwrap()) as Paddr;
+
+    // The fields of `EFI_SYSTEM_TABLE` after the 24-byte header, of which only the number of the
+    // entries and the address of the configuration table are used.
+    const NR_TABLE_ENTRIES_OFFSET: usize = 104;
+    const CONFIG_TABLE_OFFSET: usize = 112;
+    const CONFIG_TABLE_ENTRY_SIZE: usize = 24;
+
+    // SAFETY: The UEFI system table and its configuration table are in the physical memory,
+    // which is linearly mapped during boot.
+    let system_table = unsafe { phys_bytes(system_table, CONFIG_TABLE_OFFSET + 8) };
+    let nr_entries = read_u64(system_table, NR_TABLE_ENTRIES_OFFSET)? as usize;
+    let config_table = read_u64(system_table, CONFIG_TABLE_OFFSET)? as Paddr;
+    // SAFETY: Same as above.
+    let config_table = unsafe { phys_bytes(config_table, nr_entries * CONFIG_TABLE_ENTRY_SIZE) };
+    config_table
+        .chunks_exact(CONFIG_TABLE_ENTRY_SIZE)
+        .find(|entry| entry[0..16] == ACPI_20_TABLE_GUID)
+        .and_then(|entry| read_u64(entry, 16))
+        .map(|addr| addr as Paddr)
+}
+
+fn parse_root_table(rsdp: Paddr) -> Option<AcpiTables> {
+    // SAFETY: The RSDP is in the physical memory, which is linearly mapped during boot.
+    let rsdp_bytes = unsafe { phys_bytes(rsdp, RSDP_V2_SIZE) };
+    if &rsdp_bytes[0..8] != b"RSD PTR " || !is_checksum_valid(&rsdp_bytes[..RSDP_V1_SIZE]) {
+        return None;
+    }
+
+    // The XSDT is preferred if the revision is 2 or later.
+    let (root, entry_size) = if rsdp_bytes[15] >= 2 && is_checksum_valid(rsdp_bytes) {
+        (read_u64(rsdp_bytes, 24)? as Paddr, 8)
+    } else {
+        (read_u32(rsdp_bytes, 16)? as Paddr, 4)
+    };
+    let root_bytes = table_bytes(root)?;
+    let expected_signature = if entry_size == 8 { b"XSDT" } else { b"RSDT" };
+    if &root_bytes[0..4] != expected_signature {
+        return None;
+    }
+
+    let mut tables = AcpiTables {
+        rsdp,
+        root,
+        tables: [0; MAX_NR_TABLES],
+        nr_tables: 0,
+    };
+    for entry in root_bytes[HEADER_SIZE..].chunks_exact(entry_size) {
+        if tables.nr_tables == MAX_NR_TABLES {
+            break;
+        }
+        let table = if entry_size == 8 {
+            read_u64(entry, 0)? as Paddr
+        } else {
+            read_u32(entry, 0)? as Paddr
+        };
+        tables.tables[tables.nr_tables] = table;
+        tables.nr_tables += 1;
+    }
+    Some(tables)
+}
+
+/// Returns the length of the table in its header.
+fn table_len(table: Paddr) -> Option<usize> {
+    // SAFETY: The tables are in the physical memory, which is linearly mapped.
+    let header = unsafe { phys_bytes(table, HEADER_SIZE) };
+    let len = read_u32(header, 4)? as usize;
+    (len >= HEADER_SIZE).then_some(len)
+}
+
+/// Returns the bytes of the table, if its checksum is valid.
+fn table_bytes(table: Paddr) -> Option<&'static [u8]> {
+    let len = table_len(table)?;
+    // SAFETY: The tables are in the physical memory, which is linearly mapped. They are
+    // reserved (see `table_ranges`) and are never written.
+    let bytes = unsafe { phys_bytes(table, len) };
+    is_checksum_valid(bytes).then_some(bytes)
+}
+
+fn is_checksum_valid(bytes: &[u8]) -> bool {
+    bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) == 0
+}
+
+/// Returns the bytes of the physical memory.
+///
+/// # Safety
+///
+/// The physical memory must be linearly mapped and must not be written while the bytes are
+/// alive.
+unsafe fn phys_bytes(paddr: Paddr, len: usize) -> &'static [u8] {
+    // SAFETY: The safety is upheld by the caller.
+    unsafe { core::slice::from_raw_parts(paddr_to_vaddr(paddr) as *const u8, len) }
+}
+
+fn parse_addr(arg: &str) -> Option<Paddr> {
+    match arg.strip_prefix("0x") {
+        Some(hex) => Paddr::from_str_radix(hex, 16).ok(),
+        None => arg.parse().ok(),
+    }
+}
+
+/// Reads the little-endian integers in the tables, which are not necessarily aligned.
+fn read_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        bytes.get(offset..offset + 2)?.try_into().unwrap(),
+    ))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        bytes.get(offset..offset + 4)?.try_into().unwrap(),
+    ))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(
+        bytes.get(offset..offset + 8)?.try_into().unwrap(),
+    ))
+}
+
+/// Iterates over the structures of a table, each of which starts with its type and its length.
+///
+/// The types and the lengths of the structures are of `u8` in the MADT, but are of `u16` in the
+/// RHCT, so the width is given by `is_wide`. The iteration stops at the first malformed structure.
+fn structures(
+    bytes: &'static [u8],
+    start: usize,
+    is_wide: bool,
+) -> impl Iterator<Item = (u16, &'static [u8])> + Clone {
+    let mut offset = start;
+    core::iter::from_fn(move || {
+        let (type_, len) = if is_wide {
+            (
+                read_u16(bytes, offset)?,
+                read_u16(bytes, offset + 2)? as usize,
+            )
+        } else {
+            (*bytes.get(offset)? as u16, *bytes.get(offset + 1)? as usize)
+        };
+        let structure = bytes.get(offset..offset.checked_add(len)?)?;
+        if len < 2 {
+            return None;
+        }
+        offset += len;
+        Some((type_, structure))
+    })
+}
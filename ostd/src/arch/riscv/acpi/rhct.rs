@@ -0,0 +1,74 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The RISC-V Hart Capabilities Table (RHCT).
+//!
+//! The RHCT describes the time base frequency, and the capabilities of the harts as nodes, e.g.,
+//! the ISA strings and the cache block sizes. Each hart has a hart info node, which refers to the
+//! nodes of its capabilities, and is identified by the ACPI processor UID in its RINTC of the MADT.
+//!
+//! Reference: <https://uefi.org/specs/ACPI/6.5/05_ACPI_Software_Programming_Model.html#risc-v-hart-capabilities-table-rhct>
+
+use super::{find_table, madt, read_u16, read_u32, read_u64, structures};
+use crate::arch::boot::BOOT_HART_ID;
+
+const NODE_ISA_STRING: u16 = 0;
+const NODE_CMO: u16 = 1;
+const NODE_HART_INFO: u16 = 0xFFFF;
+
+fn rhct() -> Option<&'static [u8]> {
+    find_table(b"RHCT")
+}
+
+/// Returns the frequency of the `time` CSR, in Hz.
+pub(crate) fn time_base_frequency() -> Option<u64> {
+    read_u64(rhct()?, 40).filter(|freq| *freq != 0)
+}
+
+/// Returns the ISA string of the boot hart, e.g., "rv64imafdc_zicsr_zifencei".
+pub(crate) fn isa_string() -> Option<&'static str> {
+    let node = boot_hart_node(NODE_ISA_STRING)?;
+    let len = read_u16(node, 6)? as usize;
+    let isa = node.get(8..8 + len)?;
+    // The length includes the terminating null character.
+    let isa = isa.split(|byte| *byte == 0).next()?;
+    core::str::from_utf8(isa).ok()
+}
+
+/// Returns the block size of the Zicbom instructions of the boot hart, in bytes.
+pub(crate) fn cbom_block_size() -> Option<usize> {
+    let node = boot_hart_node(NODE_CMO)?;
+    let log2_size = *node.get(7)?;
+    (log2_size != 0 && log2_size < 16).then(|| 1 << log2_size)
+}
+
+/// Returns the node of the type that is referred by the hart info node of the boot hart.
+///
+/// If the boot hart has no hart info node, the first node of the type is returned, since the
+/// capabilities are usually the same for all the harts.
+fn boot_hart_node(type_: u16) -> Option<&'static [u8]> {
+    let rhct = rhct()?;
+    let nr_nodes = read_u32(rhct, 48)? as usize;
+    let nodes_offset = read_u32(rhct, 52)? as usize;
+    let mut nodes = structures(rhct, nodes_offset, true).take(nr_nodes);
+
+    let boot_hart_uid = madt::harts()
+        .find(|hart| hart.hart_id == *BOOT_HART_ID.get().unwrap())
+        .map(|hart| hart.uid);
+    let hart_info = boot_hart_uid.and_then(|uid| {
+        nodes.clone().find(|(node_type, node)| {
+            *node_type == NODE_HART_INFO && read_u32(node, 8) == Some(uid)
+        })
+    });
+
+    let Some((_, hart_info)) = hart_info else {
+        return nodes
+            .find(|(node_type, _)| *node_type == type_)
+            .map(|(_, node)| node);
+    };
+    let nr_offsets = read_u16(hart_info, 6)? as usize;
+    (0..nr_offsets)
+        .filter_map(|i| read_u32(hart_info, 12 + i * 4))
+        .filter_map(|offset| structures(rhct, offset as usize, true).next())
+        .find(|(node_type, _)| *node_type == type_)
+        .map(|(_, node)| node)
+}
@@ -0,0 +1,37 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::vec::Vec;
+
+use align_ext::AlignExt;
+
+use crate::{boot::memory_region::MemoryRegionType, io::IoMemAllocatorBuilder};
+
+/// Initializes the allocatable MMIO area based on the RISC-V memory distribution map.
+///
+/// RISC-V platforms (e.g., QEMU `virt`, StarFive JH7110) place their on-chip peripherals
+/// below the DRAM base, and PCIe host bridges may map large windows above the top of DRAM.
+/// Both areas are regarded as allocatable MMIO areas.
+pub(super) fn construct_io_mem_allocator_builder() -> IoMemAllocatorBuilder {
+    let regions = &crate::boot::EARLY_INFO.get().unwrap().memory_regions;
+    let mut ranges = Vec::with_capacity(2);
+
+    let ram_filter = regions
+        .iter()
+        .filter(|r| r.typ() != MemoryRegionType::Unknown && r.typ() != MemoryRegionType::Reserved);
+
+    // The area below the lowest memory region (e.g., UART, PLIC and SD/MMC controllers).
+    const LOW_MMIO_ALIGN: usize = 0x1000;
+    let ram_start = ram_filter.clone().map(|r| r.base()).min().unwrap();
+    ranges.push(0..ram_start.align_down(LOW_MMIO_ALIGN));
+
+    // The area above the highest memory region (e.g., 64-bit PCIe windows).
+    const HIGH_MMIO_TOP: usize = 0x100_0000_0000;
+    const HIGH_MMIO_ALIGN: usize = 0x4000_0000;
+    let ram_end = ram_filter.map(|r| r.end()).max().unwrap();
+    let mmio_start_addr = ram_end.align_up(HIGH_MMIO_ALIGN);
+    assert!(mmio_start_addr < HIGH_MMIO_TOP);
+    ranges.push(mmio_start_addr..HIGH_MMIO_TOP);
+
+    // SAFETY: The range is guaranteed not to access physical memory.
+    unsafe { IoMemAllocatorBuilder::new(ranges) }
+}
@@ -0,0 +1,783 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The EFI stub, with which the kernel can be booted as an EFI application.
+//!
+//! The image starts with a PE/COFF header (see `boot.S`), so the UEFI firmware (e.g., EDK2 or the
+//! `bootefi` command of U-Boot) can load the raw binary of the kernel (i.e., the output of
+//! `objcopy -O binary`) and enter `efi_pe_entry` (see `efi.S`). The entry copies the image to its
+//! physical address if it is loaded elsewhere, enables the boot page table, and calls
+//! [`efi_stub_main`] at its virtual address, which
+//!  - finds the device tree in the configuration table, and the boot hart ID with the
+//!    `RISCV_EFI_BOOT_PROTOCOL`;
+//!  - loads the initramfs with the `LoadFile2` protocol of the Linux initrd media device path;
+//!  - gets the memory map and exits the boot services;
+//!  - enters `_start` with a new device tree, as if the kernel is booted by SBI.
+//!
+//! As the EFI stub of Linux does, the kernel command line in the load options, the initramfs, the
+//! UEFI system table and the memory map are passed by the properties of `/chosen` in the new
+//! device tree. If the firmware does not provide a device tree (e.g., it describes the platform
+//! with ACPI), the new device tree only has `/chosen`. After booting, the memory regions are
+//! parsed from the memory map by [`parse_memory_map`] instead of the memory nodes.
+//!
+//! Reference: <https://www.kernel.org/doc/html/latest/arch/riscv/boot.html>
+
+use core::{arch::global_asm, ffi::c_void, mem::size_of, ops::Range, ptr};
+
+use fdt::{node::FdtNode, Fdt};
+
+use super::DEVICE_TREE;
+use crate::{
+    arch::kexec::fdt::{
+        FDT_BEGIN_NODE, FDT_END, FDT_END_NODE, FDT_HEADER_SIZE, FDT_LAST_COMP_VERSION, FDT_MAGIC,
+        FDT_PROP, FDT_VERSION,
+    },
+    boot::memory_region::{MemoryRegion, MemoryRegionArray, MemoryRegionType},
+    mm::{paddr_to_vaddr, Paddr, PAGE_SIZE},
+};
+
+global_asm!(include_str!("efi.S"));
+
+extern "C" {
+    fn efi_enter_kernel(hart_id: usize, device_tree: Paddr) -> !;
+}
+
+type Handle = *const c_void;
+type Status = usize;
+
+const EFI_SUCCESS: Status = 0;
+const EFI_ERROR: Status = 1 << (usize::BITS - 1);
+const EFI_UNSUPPORTED: Status = EFI_ERROR | 3;
+const EFI_BUFFER_TOO_SMALL: Status = EFI_ERROR | 5;
+
+const ALLOCATE_ANY_PAGES: u32 = 0;
+const LOADER_DATA: u32 = 2;
+
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Guid([u8; 16]);
+
+impl Guid {
+    /// Creates a GUID from its fields, which are stored in little endian except the last one.
+    const fn new(a: u32, b: u16, c: u16, d: [u8; 8]) -> Self {
+        let a = a.to_le_bytes();
+        let b = b.to_le_bytes();
+        let c = c.to_le_bytes();
+        Self([
+            a[0], a[1], a[2], a[3], b[0], b[1], c[0], c[1], d[0], d[1], d[2], d[3], d[4], d[5],
+            d[6], d[7],
+        ])
+    }
+}
+
+const DEVICE_TREE_GUID: Guid = Guid::new(
+    0xb1b621d5,
+    0xf19c,
+    0x41a5,
+    [0x83, 0x0b, 0xd9, 0x15, 0x2c, 0x69, 0xaa, 0xe0],
+);
+const RISCV_EFI_BOOT_PROTOCOL_GUID: Guid = Guid::new(
+    0xccd15fec,
+    0x6f73,
+    0x4eec,
+    [0x83, 0x95, 0x3e, 0x69, 0xe4, 0xb9, 0x40, 0xbf],
+);
+const LOADED_IMAGE_PROTOCOL_GUID: Guid = Guid::new(
+    0x5b1b31a1,
+    0x9562,
+    0x11d2,
+    [0x8e, 0x3f, 0x00, 0xa0, 0xc9, 0x69, 0x72, 0x3b],
+);
+const LOAD_FILE2_PROTOCOL_GUID: Guid = Guid::new(
+    0x4006c0c1,
+    0xfcb3,
+    0x403e,
+    [0x99, 0x6d, 0x4a, 0x6c, 0x87, 0x24, 0xe0, 0x6d],
+);
+const LINUX_EFI_INITRD_MEDIA_GUID: Guid = Guid::new(
+    0x5568e427,
+    0x68fc,
+    0x4f3d,
+    [0xac, 0x74, 0xca, 0x55, 0x52, 0x31, 0xcc, 0x68],
+);
+
+/// The vendor media device path of the initrd, which is followed by the end node.
+const INITRD_DEVICE_PATH: [u8; 24] = {
+    let mut path = [0u8; 24];
+    // MEDIA_DEVICE_PATH, MEDIA_VENDOR_DP and the length
+    path[0] = 4;
+    path[1] = 3;
+    path[2] = 20;
+    let mut i = 0;
+    while i < 16 {
+        path[4 + i] = LINUX_EFI_INITRD_MEDIA_GUID.0[i];
+        i += 1;
+    }
+    // END_DEVICE_PATH_TYPE, END_ENTIRE_DEVICE_PATH_SUBTYPE and the length
+    path[20] = 0x7f;
+    path[21] = 0xff;
+    path[22] = 4;
+    path
+};
+
+/// The system table, of which the unused fields are omitted.
+#[repr(C)]
+struct SystemTable {
+    // From the header to `ConsoleOutHandle`
+    _unused0: [usize; 8],
+    con_out: *const SimpleTextOutput,
+    // From `StandardErrorHandle` to `RuntimeServices`
+    _unused1: [usize; 3],
+    boot_services: *const BootServices,
+    nr_table_entries: usize,
+    configuration_table: *const ConfigurationTable,
+}
+
+#[repr(C)]
+struct ConfigurationTable {
+    vendor_guid: Guid,
+    vendor_table: *const c_void,
+}
+
+/// The boot services, of which the unused ones are omitted.
+#[repr(C)]
+struct BootServices {
+    // From the header to `RestoreTPL`
+    _unused0: [usize; 5],
+    allocate_pages: unsafe extern "efiapi" fn(u32, u32, usize, *mut u64) -> Status,
+    // `FreePages`
+    _unused1: usize,
+    get_memory_map:
+        unsafe extern "efiapi" fn(*mut usize, *mut u8, *mut usize, *mut usize, *mut u32) -> Status,
+    allocate_pool: unsafe extern "efiapi" fn(u32, usize, *mut *mut u8) -> Status,
+    // From `FreePool` to `UninstallProtocolInterface`
+    _unused2: [usize; 10],
+    handle_protocol: unsafe extern "efiapi" fn(Handle, *const Guid, *mut *const c_void) -> Status,
+    // From `Reserved` to `LocateHandle`
+    _unused3: [usize; 3],
+    locate_device_path:
+        unsafe extern "efiapi" fn(*const Guid, *mut *const c_void, *mut Handle) -> Status,
+    // From `InstallConfigurationTable` to `UnloadImage`
+    _unused4: [usize; 5],
+    exit_boot_services: unsafe extern "efiapi" fn(Handle, usize) -> Status,
+    // From `GetNextMonotonicCount` to `LocateHandleBuffer`
+    _unused5: [usize; 10],
+    locate_protocol:
+        unsafe extern "efiapi" fn(*const Guid, *const c_void, *mut *const c_void) -> Status,
+}
+
+#[repr(C)]
+struct SimpleTextOutput {
+    _reset: usize,
+    output_string: unsafe extern "efiapi" fn(*const SimpleTextOutput, *const u16) -> Status,
+}
+
+#[repr(C)]
+struct LoadedImage {
+    // From `Revision` to `Reserved`
+    _unused: [usize; 6],
+    load_options_size: u32,
+    load_options: *const u16,
+}
+
+#[repr(C)]
+struct RiscvEfiBootProtocol {
+    _revision: u64,
+    get_boot_hartid: unsafe extern "efiapi" fn(*const RiscvEfiBootProtocol, *mut usize) -> Status,
+}
+
+#[repr(C)]
+struct LoadFile2Protocol {
+    load_file: unsafe extern "efiapi" fn(
+        *const LoadFile2Protocol,
+        *const c_void,
+        bool,
+        *mut usize,
+        *mut u8,
+    ) -> Status,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct MemoryDescriptor {
+    type_: u32,
+    phys_start: u64,
+    _virt_start: u64,
+    nr_pages: u64,
+    _attribute: u64,
+}
+
+/// The entry point of the EFI stub, which is called by `efi_pe_entry`.
+///
+/// It returns to the firmware only on failures.
+#[no_mangle]
+unsafe extern "C" fn efi_stub_main(image: Handle, system_table: *const SystemTable) -> Status {
+    // SAFETY: The system table and the boot services are valid before the boot services are
+    // exited, and the memory is identically mapped by the boot page table.
+    let stub = unsafe {
+        EfiStub {
+            image,
+            system_table: &*system_table,
+            boot_services: &*(*system_table).boot_services,
+        }
+    };
+    stub.run()
+}
+
+struct EfiStub {
+    image: Handle,
+    system_table: &'static SystemTable,
+    boot_services: &'static BootServices,
+}
+
+impl EfiStub {
+    /// Runs the EFI stub, which returns only on failures.
+    ///
+    /// The kernel heap and the CPU-local storage are not available, so only the memory allocated
+    /// by the boot services is used.
+    fn run(&self) -> Status {
+        let device_tree = self.find_device_tree();
+        let Some(hart_id) = self.boot_hart_id(device_tree.as_ref()) else {
+            self.print("EFI stub: failed to get the boot hart ID\n");
+            return EFI_UNSUPPORTED;
+        };
+        let bootargs = self.load_options();
+        let initrd = self.load_initrd();
+
+        // The new device tree is built in a buffer of the blob and a buffer of the strings, with
+        // enough space for the new properties.
+        let max_size = device_tree.as_ref().map_or(0, |fdt| fdt.total_size())
+            + bootargs.map_or(0, |bootargs| bootargs.len())
+            + PAGE_SIZE;
+        let blob = match self.allocate_pages(max_size) {
+            Ok(blob) => blob,
+            Err(status) => return status,
+        };
+        let strings = match self.allocate_pool(max_size) {
+            Ok(strings) => strings,
+            Err(status) => return status,
+        };
+
+        let system_table = (self.system_table as *const SystemTable as u64).to_be_bytes();
+        let initrd_start = initrd
+            .as_ref()
+            .map(|range| (range.start as u64).to_be_bytes());
+        let initrd_end = initrd
+            .as_ref()
+            .map(|range| (range.end as u64).to_be_bytes());
+
+        // The buffer of the memory map should be large enough to hold the descriptors of the
+        // allocations of itself.
+        let mut map_size = 0;
+        let mut map_key = 0;
+        let mut desc_size = 0;
+        let mut desc_version = 0;
+        // SAFETY: The memory map is not written if the buffer is too small.
+        let status = unsafe {
+            (self.boot_services.get_memory_map)(
+                &mut map_size,
+                ptr::null_mut(),
+                &mut map_key,
+                &mut desc_size,
+                &mut desc_version,
+            )
+        };
+        if status != EFI_BUFFER_TOO_SMALL {
+            return status;
+        }
+        let map = match self.allocate_pool(map_size + 8 * desc_size) {
+            Ok(map) => map,
+            Err(status) => return status,
+        };
+
+        // The memory map may change between getting it and exiting the boot services, in which
+        // case exiting the boot services fails and should be retried with the new memory map.
+        let mut status = EFI_SUCCESS;
+        for _ in 0..2 {
+            let mut map_size = map.len();
+            // SAFETY: The buffer is valid for writing `map_size` bytes.
+            status = unsafe {
+                (self.boot_services.get_memory_map)(
+                    &mut map_size,
+                    map.as_mut_ptr(),
+                    &mut map_key,
+                    &mut desc_size,
+                    &mut desc_version,
+                )
+            };
+            if status != EFI_SUCCESS {
+                return status;
+            }
+
+            let mmap_start = (map.as_ptr() as u64).to_be_bytes();
+            let mmap_size = (map_size as u32).to_be_bytes();
+            let mmap_desc_size = (desc_size as u32).to_be_bytes();
+            let mmap_desc_version = desc_version.to_be_bytes();
+            let chosen = [
+                ("bootargs", bootargs),
+                (
+                    "linux,initrd-start",
+                    initrd_start.as_ref().map(|val| &val[..]),
+                ),
+                ("linux,initrd-end", initrd_end.as_ref().map(|val| &val[..])),
+                ("linux,uefi-system-table", Some(&system_table[..])),
+                ("linux,uefi-mmap-start", Some(&mmap_start[..])),
+                ("linux,uefi-mmap-size", Some(&mmap_size[..])),
+                ("linux,uefi-mmap-desc-size", Some(&mmap_desc_size[..])),
+                ("linux,uefi-mmap-desc-ver", Some(&mmap_desc_version[..])),
+            ];
+            if write_device_tree(device_tree.as_ref(), blob, strings, &chosen).is_none() {
+                self.print("EFI stub: failed to build the device tree\n");
+                return EFI_BUFFER_TOO_SMALL;
+            }
+
+            // SAFETY: The image handle is passed by the firmware, and the map key is the
+            // latest one.
+            status = unsafe { (self.boot_services.exit_boot_services)(self.image, map_key) };
+            if status == EFI_SUCCESS {
+                // SAFETY: The boot services are exited, and the device tree is built. The kernel
+                // is at its physical address.
+                unsafe { efi_enter_kernel(hart_id, blob.as_ptr() as Paddr) };
+            }
+        }
+        status
+    }
+
+    fn find_device_tree(&self) -> Option<Fdt<'static>> {
+        let table = self.find_configuration_table(&DEVICE_TREE_GUID)?;
+        // SAFETY: The device tree is provided by the firmware, which is not freed before the
+        // kernel reserves the memory regions.
+        unsafe { Fdt::from_ptr(table as *const u8).ok() }
+    }
+
+    fn find_configuration_table(&self, guid: &Guid) -> Option<*const c_void> {
+        // SAFETY: The configuration table has `nr_table_entries` entries.
+        let tables = unsafe {
+            core::slice::from_raw_parts(
+                self.system_table.configuration_table,
+                self.system_table.nr_table_entries,
+            )
+        };
+        tables
+            .iter()
+            .find(|table| table.vendor_guid == *guid)
+            .map(|table| table.vendor_table)
+    }
+
+    /// Returns the boot hart ID from the `RISCV_EFI_BOOT_PROTOCOL`, or from the `boot-hartid`
+    /// property of `/chosen`, which is set by the older firmware.
+    fn boot_hart_id(&self, device_tree: Option<&Fdt>) -> Option<usize> {
+        if let Some(protocol) =
+            self.locate_protocol::<RiscvEfiBootProtocol>(&RISCV_EFI_BOOT_PROTOCOL_GUID)
+        {
+            let mut hart_id = 0;
+            // SAFETY: The protocol is located and `hart_id` is valid for writing.
+            let status = unsafe { (protocol.get_boot_hartid)(protocol, &mut hart_id) };
+            if status == EFI_SUCCESS {
+                return Some(hart_id);
+            }
+        }
+
+        device_tree?
+            .find_node("/chosen")?
+            .property("boot-hartid")?
+            .as_usize()
+    }
+
+    /// Returns the kernel command line in the load options, which ends with the null character.
+    ///
+    /// The UCS-2 characters are converted to ASCII, as Linux does. If there is no load option, the
+    /// command line in the device tree is kept.
+    fn load_options(&self) -> Option<&'static [u8]> {
+        let loaded_image =
+            self.handle_protocol::<LoadedImage>(self.image, &LOADED_IMAGE_PROTOCOL_GUID)?;
+        if loaded_image.load_options.is_null() {
+            return None;
+        }
+        // SAFETY: The load options are valid for reading `load_options_size` bytes.
+        let options = unsafe {
+            core::slice::from_raw_parts(
+                loaded_image.load_options,
+                loaded_image.load_options_size as usize / size_of::<u16>(),
+            )
+        };
+        let options = options.split(|c| *c == 0).next().unwrap_or_default();
+        if options.iter().all(|c| *c == b' ' as u16) {
+            return None;
+        }
+
+        let bootargs = self.allocate_pool(options.len() + 1).ok()?;
+        for (byte, c) in bootargs.iter_mut().zip(options) {
+            *byte = if *c < 0x80 { *c as u8 } else { b'?' };
+        }
+        bootargs[options.len()] = 0;
+        Some(bootargs)
+    }
+
+    /// Loads the initramfs with the `LoadFile2` protocol of the initrd media device path, which
+    /// is installed by the firmware (e.g., with the `initrd` command of GRUB or the
+    /// `efi_initrd` of U-Boot).
+    fn load_initrd(&self) -> Option<Range<Paddr>> {
+        let mut device_path = INITRD_DEVICE_PATH.as_ptr() as *const c_void;
+        let mut handle = ptr::null();
+        // SAFETY: The device path is valid and ends with the end node.
+        let status = unsafe {
+            (self.boot_services.locate_device_path)(
+                &LOAD_FILE2_PROTOCOL_GUID,
+                &mut device_path,
+                &mut handle,
+            )
+        };
+        if status != EFI_SUCCESS {
+            return None;
+        }
+        let load_file2 =
+            self.handle_protocol::<LoadFile2Protocol>(handle, &LOAD_FILE2_PROTOCOL_GUID)?;
+        let device_path = INITRD_DEVICE_PATH.as_ptr() as *const c_void;
+
+        let mut size = 0;
+        // SAFETY: The file is not loaded if the buffer is null.
+        let status = unsafe {
+            (load_file2.load_file)(load_file2, device_path, false, &mut size, ptr::null_mut())
+        };
+        if status != EFI_BUFFER_TOO_SMALL || size == 0 {
+            return None;
+        }
+        let initrd = self.allocate_pages(size).ok()?;
+        // SAFETY: The buffer is valid for writing `size` bytes.
+        let status = unsafe {
+            (load_file2.load_file)(
+                load_file2,
+                device_path,
+                false,
+                &mut size,
+                initrd.as_mut_ptr(),
+            )
+        };
+        if status != EFI_SUCCESS {
+            self.print("EFI stub: failed to load the initramfs\n");
+            return None;
+        }
+
+        let start = initrd.as_ptr() as Paddr;
+        Some(start..start + size)
+    }
+
+    fn locate_protocol<T>(&self, guid: &Guid) -> Option<&'static T> {
+        let mut interface = ptr::null();
+        // SAFETY: The GUID is valid and `interface` is valid for writing.
+        let status =
+            unsafe { (self.boot_services.locate_protocol)(guid, ptr::null(), &mut interface) };
+        if status != EFI_SUCCESS || interface.is_null() {
+            return None;
+        }
+        // SAFETY: The interface of the protocol of the GUID is of `T`.
+        Some(unsafe { &*(interface as *const T) })
+    }
+
+    fn handle_protocol<T>(&self, handle: Handle, guid: &Guid) -> Option<&'static T> {
+        let mut interface = ptr::null();
+        // SAFETY: The GUID is valid and `interface` is valid for writing.
+        let status = unsafe { (self.boot_services.handle_protocol)(handle, guid, &mut interface) };
+        if status != EFI_SUCCESS || interface.is_null() {
+            return None;
+        }
+        // SAFETY: The interface of the protocol of the GUID is of `T`.
+        Some(unsafe { &*(interface as *const T) })
+    }
+
+    /// Allocates pages of the loader data, which are kept after the boot services are exited.
+    fn allocate_pages(&self, size: usize) -> Result<&'static mut [u8], Status> {
+        let mut addr = 0;
+        // SAFETY: `addr` is valid for writing.
+        let status = unsafe {
+            (self.boot_services.allocate_pages)(
+                ALLOCATE_ANY_PAGES,
+                LOADER_DATA,
+                size.div_ceil(PAGE_SIZE),
+                &mut addr,
+            )
+        };
+        if status != EFI_SUCCESS {
+            self.print("EFI stub: failed to allocate pages\n");
+            return Err(status);
+        }
+        // SAFETY: The pages are allocated and are identically mapped.
+        Ok(unsafe { core::slice::from_raw_parts_mut(addr as *mut u8, size) })
+    }
+
+    /// Allocates a buffer from the pool of the loader data.
+    fn allocate_pool(&self, size: usize) -> Result<&'static mut [u8], Status> {
+        let mut buffer = ptr::null_mut();
+        // SAFETY: `buffer` is valid for writing.
+        let status = unsafe { (self.boot_services.allocate_pool)(LOADER_DATA, size, &mut buffer) };
+        if status != EFI_SUCCESS {
+            self.print("EFI stub: failed to allocate memory\n");
+            return Err(status);
+        }
+        // SAFETY: The buffer is allocated and is identically mapped.
+        Ok(unsafe { core::slice::from_raw_parts_mut(buffer, size) })
+    }
+
+    fn print(&self, message: &str) {
+        let con_out = self.system_table.con_out;
+        if con_out.is_null() {
+            return;
+        }
+
+        let mut buffer = [0u16; 64];
+        let mut len = 0;
+        let chars = message.bytes().flat_map(|byte| match byte {
+            b'\n' => [Some(b'\r'), Some(b'\n')],
+            byte => [Some(byte), None],
+        });
+        for byte in chars.flatten() {
+            buffer[len] = byte as u16;
+            len += 1;
+            if len == buffer.len() - 2 {
+                buffer[len] = 0;
+                // SAFETY: The string ends with the null character.
+                unsafe { ((*con_out).output_string)(con_out, buffer.as_ptr()) };
+                len = 0;
+            }
+        }
+        buffer[len] = 0;
+        // SAFETY: The string ends with the null character.
+        unsafe { ((*con_out).output_string)(con_out, buffer.as_ptr()) };
+    }
+}
+
+/// Writes a copy of the device tree to `blob`, with which the properties of `/chosen` are set.
+///
+/// Unlike [`build_device_tree`], a property is kept as it is if its value in `chosen` is `None`.
+/// The buffer of `strings` should be at least as large as the strings block. If there is no device
+/// tree, a device tree with only `/chosen` is written.
+///
+/// [`build_device_tree`]: crate::arch::kexec::fdt::build_device_tree
+fn write_device_tree(
+    device_tree: Option<&Fdt>,
+    blob: &mut [u8],
+    strings: &mut [u8],
+    chosen: &[(&str, Option<&[u8]>)],
+) -> Option<usize> {
+    let (header, rest) = blob.split_at_mut_checked(FDT_HEADER_SIZE)?;
+
+    let mut reservations = FdtWriter::new(rest);
+    for reservation in device_tree
+        .into_iter()
+        .flat_map(|device_tree| device_tree.memory_reservations())
+    {
+        reservations.bytes(&(reservation.address() as u64).to_be_bytes())?;
+        reservations.bytes(&(reservation.size() as u64).to_be_bytes())?;
+    }
+    reservations.bytes(&[0u8; 16])?;
+    let reservations_len = reservations.len;
+
+    let mut writer = FdtWriter::new(&mut rest[reservations_len..]);
+    writer.strings = strings;
+    match device_tree {
+        Some(device_tree) => writer.node(device_tree.find_node("/")?, 0, chosen)?,
+        None => {
+            writer.token(FDT_BEGIN_NODE)?;
+            writer.bytes(b"\0")?;
+            writer.node_end(0, false, chosen)?;
+        }
+    }
+    writer.token(FDT_END)?;
+    let structure_len = writer.len;
+    let strings_len = writer.strings_len;
+
+    let off_mem_rsvmap = FDT_HEADER_SIZE;
+    let off_dt_struct = off_mem_rsvmap + reservations_len;
+    let off_dt_strings = off_dt_struct + structure_len;
+    let total_size = off_dt_strings + strings_len;
+    rest.get_mut(off_dt_strings - FDT_HEADER_SIZE..total_size - FDT_HEADER_SIZE)?
+        .copy_from_slice(&strings[..strings_len]);
+
+    for (index, field) in [
+        FDT_MAGIC,
+        total_size as u32,
+        off_dt_struct as u32,
+        off_dt_strings as u32,
+        off_mem_rsvmap as u32,
+        FDT_VERSION,
+        FDT_LAST_COMP_VERSION,
+        // The boot CPU is not used by RISC-V kernels, which get the hart ID from `a0`.
+        0,
+        strings_len as u32,
+        structure_len as u32,
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        header[index * 4..index * 4 + 4].copy_from_slice(&field.to_be_bytes());
+    }
+    Some(total_size)
+}
+
+/// A writer of the structure block and the strings block of a device tree in fixed buffers.
+struct FdtWriter<'a> {
+    buffer: &'a mut [u8],
+    len: usize,
+    strings: &'a mut [u8],
+    strings_len: usize,
+}
+
+impl<'a> FdtWriter<'a> {
+    fn new(buffer: &'a mut [u8]) -> Self {
+        Self {
+            buffer,
+            len: 0,
+            strings: &mut [],
+            strings_len: 0,
+        }
+    }
+
+    fn node(
+        &mut self,
+        node: FdtNode,
+        depth: usize,
+        chosen: &[(&str, Option<&[u8]>)],
+    ) -> Option<()> {
+        // The name of the root node is empty in the blob.
+        let name = if depth == 0 { "" } else { node.name };
+        let is_chosen = depth == 1 && name == "chosen";
+
+        self.token(FDT_BEGIN_NODE)?;
+        self.bytes(name.as_bytes())?;
+        self.bytes(b"\0")?;
+
+        for property in node.properties() {
+            if is_chosen
+                && chosen
+                    .iter()
+                    .any(|(name, value)| *name == property.name && value.is_some())
+            {
+                continue;
+            }
+            self.property(property.name, property.value)?;
+        }
+        if is_chosen {
+            self.chosen_properties(chosen)?;
+        }
+
+        for child in node.children() {
+            self.node(child, depth + 1, chosen)?;
+        }
+        let has_chosen = node.children().any(|child| child.name == "chosen");
+        self.node_end(depth, has_chosen, chosen)
+    }
+
+    /// Ends a node, before which `/chosen` is added if it is the root node without `/chosen`.
+    fn node_end(
+        &mut self,
+        depth: usize,
+        has_chosen: bool,
+        chosen: &[(&str, Option<&[u8]>)],
+    ) -> Option<()> {
+        if depth == 0 && !has_chosen {
+            self.token(FDT_BEGIN_NODE)?;
+            self.bytes(b"chosen\0")?;
+            self.chosen_properties(chosen)?;
+            self.token(FDT_END_NODE)?;
+        }
+        self.token(FDT_END_NODE)
+    }
+
+    fn chosen_properties(&mut self, chosen: &[(&str, Option<&[u8]>)]) -> Option<()> {
+        for (name, value) in chosen {
+            if let Some(value) = value {
+                self.property(name, value)?;
+            }
+        }
+        Some(())
+    }
+
+    fn property(&mut self, name: &str, value: &[u8]) -> Option<()> {
+        let name_offset = self.string_offset(name)?;
+        self.token(FDT_PROP)?;
+        self.bytes(&(value.len() as u32).to_be_bytes())?;
+        self.bytes(&name_offset.to_be_bytes())?;
+        self.bytes(value)
+    }
+
+    fn string_offset(&mut self, name: &str) -> Option<u32> {
+        let mut offset = 0;
+        for string in self.strings[..self.strings_len].split(|byte| *byte == 0) {
+            if string == name.as_bytes() {
+                return Some(offset as u32);
+            }
+            offset += string.len() + 1;
+        }
+
+        let offset = self.strings_len;
+        let end = offset + name.len() + 1;
+        let string = self.strings.get_mut(offset..end)?;
+        string[..name.len()].copy_from_slice(name.as_bytes());
+        string[name.len()] = 0;
+        self.strings_len = end;
+        Some(offset as u32)
+    }
+
+    fn token(&mut self, token: u32) -> Option<()> {
+        self.bytes(&token.to_be_bytes())
+    }
+
+    /// Writes the bytes, which are padded to the 4-byte alignment.
+    fn bytes(&mut self, bytes: &[u8]) -> Option<()> {
+        let end = self.len + bytes.len();
+        let padded_end = end.next_multiple_of(4);
+        let buffer = self.buffer.get_mut(self.len..padded_end)?;
+        buffer[..bytes.len()].copy_from_slice(bytes);
+        buffer[bytes.len()..].fill(0);
+        self.len = padded_end;
+        Some(())
+    }
+}
+
+/// Parses the memory map that is passed by the EFI stub.
+///
+/// Returns `false` if the kernel is not booted by the EFI stub.
+pub(super) fn parse_memory_map(regions: &mut MemoryRegionArray) -> bool {
+    let Some(chosen) = DEVICE_TREE.get().unwrap().find_node("/chosen") else {
+        return false;
+    };
+    let property = |name| {
+        chosen
+            .property(name)
+            .and_then(|property| property.as_usize())
+    };
+    let (Some(start), Some(size), Some(desc_size)) = (
+        property("linux,uefi-mmap-start"),
+        property("linux,uefi-mmap-size"),
+        property("linux,uefi-mmap-desc-size"),
+    ) else {
+        return false;
+    };
+    if desc_size < size_of::<MemoryDescriptor>() {
+        return false;
+    }
+
+    for offset in (0..size).step_by(desc_size) {
+        // SAFETY: The memory map is in the physical memory, which is linearly mapped during boot.
+        // It is not overwritten before the memory regions are parsed.
+        let desc =
+            unsafe { (paddr_to_vaddr(start + offset) as *const MemoryDescriptor).read_unaligned() };
+        let typ = match desc.type_ {
+            // The loader code and data include the kernel, the initramfs and the device tree,
+            // which are reserved separately.
+            1..=4 | 7 => MemoryRegionType::Usable,
+            9 => MemoryRegionType::Reclaimable,
+            10 => MemoryRegionType::NonVolatileSleep,
+            8 => MemoryRegionType::BadMemory,
+            // The unaccepted memory is not supported.
+            15 => continue,
+            _ => MemoryRegionType::Reserved,
+        };
+        regions.push(MemoryRegion::new(
+            desc.phys_start as usize,
+            desc.nr_pages as usize * PAGE_SIZE,
+            typ,
+        ));
+    }
+    true
+}
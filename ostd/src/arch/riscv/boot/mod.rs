@@ -2,20 +2,24 @@
 
 //! The RISC-V boot module defines the entrypoints of Asterinas.
 
+mod efi;
 pub mod smp;
 
-use core::arch::global_asm;
+use core::{arch::global_asm, ops::Range};
 
+use align_ext::AlignExt;
 use fdt::Fdt;
 use spin::Once;
 
 use crate::{
+    arch::{acpi, hart, kexec::crash, mm::firmware},
     boot::{
         memory_region::{MemoryRegion, MemoryRegionArray, MemoryRegionType},
         BootloaderAcpiArg, BootloaderFramebufferArg,
     },
     early_println,
-    mm::paddr_to_vaddr,
+    mm::{paddr_to_vaddr, Paddr, PAGE_SIZE},
+    util::range_difference,
 };
 
 global_asm!(include_str!("boot.S"));
@@ -23,6 +27,9 @@ global_asm!(include_str!("boot.S"));
 /// The Flattened Device Tree of the platform.
 pub static DEVICE_TREE: Once<Fdt> = Once::new();
 
+/// The ID of the boot hart.
+pub(crate) static BOOT_HART_ID: Once<usize> = Once::new();
+
 fn parse_bootloader_name() -> &'static str {
     "Unknown"
 }
@@ -42,23 +49,60 @@ fn parse_initramfs() -> Option<&'static [u8]> {
 }
 
 fn parse_acpi_arg() -> BootloaderAcpiArg {
-    BootloaderAcpiArg::NotProvided
+    acpi::rsdp().map_or(BootloaderAcpiArg::NotProvided, BootloaderAcpiArg::Rsdp)
 }
 
 fn parse_framebuffer_info() -> Option<BootloaderFramebufferArg> {
     None
 }
 
-fn parse_memory_regions() -> MemoryRegionArray {
+fn parse_memory_regions(device_tree: Range<Paddr>) -> MemoryRegionArray {
     let mut regions = MemoryRegionArray::new();
 
-    for region in DEVICE_TREE.get().unwrap().memory().regions() {
+    // The capture kernel can only use the memory that is not used by the crashed kernel.
+    let usable_range = crash::parse_usable_memory_range();
+
+    // If the kernel is booted by the EFI stub, the memory map replaces the memory nodes.
+    let is_efi = efi::parse_memory_map(&mut regions);
+
+    // The device tree is used after the frame allocator is initialized, but it may be in the
+    // usable memory (e.g., if it is built by the EFI stub), so it is reserved.
+    regions.push(MemoryRegion::new(
+        device_tree.start.align_down(PAGE_SIZE),
+        device_tree.end.align_up(PAGE_SIZE) - device_tree.start.align_down(PAGE_SIZE),
+        MemoryRegionType::Reserved,
+    ));
+
+    // The device tree passed with the ACPI tables may only have the `/chosen` node.
+    let device_tree_memory = DEVICE_TREE
+        .get()
+        .unwrap()
+        .find_node("/memory")
+        .filter(|_| !is_efi)
+        .map(|_| DEVICE_TREE.get().unwrap().memory());
+    for region in device_tree_memory
+        .iter()
+        .flat_map(|memory| memory.regions())
+    {
         if region.size.unwrap_or(0) > 0 {
             regions.push(MemoryRegion::new(
                 region.starting_address as usize,
                 region.size.unwrap(),
                 MemoryRegionType::Usable,
             ));
+
+            let Some(usable_range) = usable_range.as_ref() else {
+                continue;
+            };
+            let start = region.starting_address as usize;
+            let range = start..start + region.size.unwrap();
+            for unusable in range_difference(&range, usable_range) {
+                regions.push(MemoryRegion::new(
+                    unusable.start,
+                    unusable.len(),
+                    MemoryRegionType::Reserved,
+                ));
+            }
         }
     }
 
@@ -76,6 +120,24 @@ fn parse_memory_regions() -> MemoryRegionArray {
         }
     }
 
+    // Add the memory of the firmware, which is protected by PMP.
+    for range in firmware::ranges() {
+        regions.push(MemoryRegion::new(
+            range.start,
+            range.len(),
+            MemoryRegionType::Reserved,
+        ));
+    }
+
+    // Add the ACPI tables, which are read after the frame allocator is initialized.
+    for table in acpi::table_ranges() {
+        regions.push(MemoryRegion::new(
+            table.start,
+            table.len(),
+            MemoryRegionType::Reclaimable,
+        ));
+    }
+
     // Add the kernel region.
     regions.push(MemoryRegion::kernel());
 
@@ -88,6 +150,26 @@ fn parse_memory_regions() -> MemoryRegionArray {
         ));
     }
 
+    // Add the ELF core header if this is a capture kernel.
+    let cmdline = parse_kernel_commandline();
+    if let Some(header) = crash::parse_elf_core_header(cmdline) {
+        regions.push(MemoryRegion::new(
+            header.start,
+            header.len(),
+            MemoryRegionType::Reserved,
+        ));
+    }
+
+    // Add the crash kernel region. This should be done after all the other regions are added,
+    // so that the crash kernel region does not overlap them.
+    if let Some(region) = crash::reserve_crash_kernel(cmdline, &regions, &[device_tree]) {
+        regions.push(MemoryRegion::new(
+            region.start,
+            region.len(),
+            MemoryRegionType::Reserved,
+        ));
+    }
+
     regions.into_non_overlapping()
 }
 
@@ -100,12 +182,18 @@ fn parse_initramfs_range() -> Option<(usize, usize)> {
 
 /// The entry point of the Rust code portion of Asterinas.
 #[no_mangle]
-pub extern "C" fn riscv_boot(_hart_id: usize, device_tree_paddr: usize) -> ! {
+pub extern "C" fn riscv_boot(hart_id: usize, device_tree_paddr: usize) -> ! {
     early_println!("Enter riscv_boot");
 
+    BOOT_HART_ID.call_once(|| hart_id);
+
     let device_tree_ptr = paddr_to_vaddr(device_tree_paddr) as *const u8;
     let fdt = unsafe { fdt::Fdt::from_ptr(device_tree_ptr).unwrap() };
+    let device_tree = device_tree_paddr..device_tree_paddr + fdt.total_size();
     DEVICE_TREE.call_once(|| fdt);
+    acpi::init(parse_kernel_commandline());
+    hart::init(hart_id);
+    firmware::init();
 
     use crate::boot::{call_ostd_main, EarlyBootInfo, EARLY_INFO};
 
@@ -115,7 +203,7 @@ pub extern "C" fn riscv_boot(_hart_id: usize, device_tree_paddr: usize) -> ! {
         initramfs: parse_initramfs(),
         acpi_arg: parse_acpi_arg(),
         framebuffer_arg: parse_framebuffer_info(),
-        memory_regions: parse_memory_regions(),
+        memory_regions: parse_memory_regions(device_tree),
     });
 
     call_ostd_main();
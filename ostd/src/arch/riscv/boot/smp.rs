@@ -2,10 +2,95 @@
 
 //! Multiprocessor Boot Support
 
+use core::{
+    ops::Range,
+    ptr::addr_of_mut,
+    sync::atomic::{fence, Ordering},
+};
+
+use crate::{
+    arch::{hart, timer::sync},
+    boot::smp::PerApRawInfo,
+    cpu::{cpu_features, CpuFeatures, CpuId},
+    mm::{kspace::kernel_loaded_offset, Paddr},
+};
+
+/// Counts the number of processors.
+///
+/// This function needs to be called after the mapping of the hart IDs is built.
 pub(crate) fn count_processors() -> Option<u32> {
-    Some(1)
+    Some(hart::nr_harts() as u32)
 }
 
-pub(crate) fn bringup_all_aps(_num_cpus: u32) {
-    // TODO
+/// The maximum number of the harts, which should be the same as `MAX_NR_HARTS` in `boot.S`.
+const MAX_NR_HARTS: usize = 256;
+
+extern "C" {
+    fn _start_secondary();
+
+    /// The boot information of the APs, which is read by `_start_secondary` in `boot.S`.
+    ///
+    /// Each entry is the CPU ID and the pointer to the [`PerApRawInfo`] of the AP, the latter of
+    /// which is null until the entry is filled. **Update the assembly code if the layout is
+    /// changed!**
+    static mut __ap_boot_info_by_hart: [[usize; 2]; MAX_NR_HARTS];
+}
+
+/// Brings up all application processors.
+///
+/// # Safety
+///
+/// The caller must ensure that
+/// 1. we're in the boot context of the BSP,
+/// 2. all APs have not yet been booted, and
+/// 3. the arguments are valid to boot APs.
+pub(crate) unsafe fn bringup_all_aps(info_ptr: *mut PerApRawInfo, _pt_ptr: Paddr, num_cpus: u32) {
+    let start_paddr = _start_secondary as usize - kernel_loaded_offset();
+    let has_hsm = cpu_features().contains(CpuFeatures::SBI_HSM);
+
+    for cpu_id in 1..num_cpus {
+        let hart_id = hart::hart_id(CpuId::try_from(cpu_id as usize).unwrap());
+        if hart_id >= MAX_NR_HARTS {
+            log::warn!(
+                "Hart {} is not brought up: the hart ID is too large",
+                hart_id
+            );
+            continue;
+        }
+
+        // SAFETY: The index is in range. The entry is only written here, while the AP may be
+        // spinning on it, so it is written volatilely and the CPU ID is visible before the
+        // pointer. The pointer is in range, as guaranteed by the caller.
+        unsafe {
+            let entry = addr_of_mut!(__ap_boot_info_by_hart[hart_id]);
+            addr_of_mut!((*entry)[0]).write_volatile(cpu_id as usize);
+            fence(Ordering::Release);
+            let raw_info = info_ptr.add(cpu_id as usize - 1);
+            addr_of_mut!((*entry)[1]).write_volatile(raw_info as usize);
+        }
+
+        // If the firmware starts all the harts, the AP is already waiting for its entry.
+        if has_hsm {
+            match sbi_rt::hart_start(hart_id, start_paddr, 0).into_result() {
+                Ok(_) | Err(sbi_rt::Error::AlreadyAvailable) => {}
+                Err(err) => log::warn!("Hart {} is not started: {:?}", hart_id, err),
+            }
+        }
+    }
+
+    // The APs measure their time against the BSP before they are marked as started.
+    sync::sync_with_aps(num_cpus - 1);
+}
+
+/// Returns the physical memory range of the boot stack of the BSP.
+///
+/// The stack is in the kernel image and is never used after the BSP switches to its first task.
+pub(crate) fn bsp_boot_stack() -> Option<Range<Paddr>> {
+    extern "C" {
+        fn boot_stack_bottom();
+        fn boot_stack_top();
+    }
+
+    let offset = kernel_loaded_offset();
+    Some(boot_stack_bottom as usize - offset..boot_stack_top as usize - offset)
 }
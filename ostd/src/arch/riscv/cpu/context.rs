@@ -4,7 +4,7 @@
 
 use core::{arch::asm, fmt::Debug, sync::atomic::{AtomicBool, Ordering}};
 
-use riscv::register::scause::{Exception, Trap};
+use riscv::register::scause::{Exception, Interrupt, Trap};
 
 pub use crate::arch::riscv::trap::GeneralRegs as RawGeneralRegs;
 use crate::{
@@ -237,10 +237,32 @@ impl UserContext {
         self.tp()
     }
 
-    /// Activates thread-local storage pointer on the current CPU.
-    pub fn activate_tls_pointer(&self) {
-        // No-op
+    /// Returns whether the user space runs in the 32-bit mode (i.e., `sstatus.UXL` is 32).
+    pub fn is_compat_mode(&self) -> bool {
+        (self.user_context.sstatus >> SSTATUS_UXL_SHIFT) & SSTATUS_UXL_MASK == UXL_32
+    }
+
+    /// Sets whether the user space runs in the 32-bit mode.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the 32-bit mode is requested but not supported by the CPU (see
+    /// [`is_compat_mode_supported`]).
+    pub fn set_compat_mode(&mut self, is_compat: bool) {
+        assert!(!is_compat || is_compat_mode_supported());
+
+        let uxl = if is_compat { UXL_32 } else { UXL_64 };
+        let sstatus = &mut self.user_context.sstatus;
+        *sstatus &= !(SSTATUS_UXL_MASK << SSTATUS_UXL_SHIFT);
+        *sstatus |= uxl << SSTATUS_UXL_SHIFT;
     }
+
+    /// Activates thread-local storage pointer on the current CPU.
+    ///
+    /// On RISC-V, the thread-local storage pointer is the `tp` register, which is restored
+    /// from the user context whenever the CPU returns to the user space. The kernel `tp` is
+    /// saved and restored separately, so nothing needs to be done here.
+    pub fn activate_tls_pointer(&self) {}
 }
 
 impl UserContextApiInternal for UserContext {
@@ -251,6 +273,12 @@ impl UserContextApiInternal for UserContext {
         let ret = loop {
             self.user_context.run();
             match riscv::register::scause::read().cause() {
+                Trap::Interrupt(Interrupt::SupervisorExternal) => {
+                    crate::arch::irq::handle_external_interrupt(&self.as_trap_frame());
+                }
+                Trap::Interrupt(Interrupt::SupervisorSoft) => {
+                    crate::arch::irq::handle_software_interrupt(&self.as_trap_frame());
+                }
                 Trap::Interrupt(_) => todo!(),
                 Trap::Exception(Exception::UserEnvCall) => {
                     self.user_context.sepc += 4;
@@ -368,3 +396,46 @@ cpu_context_impl_getter_setter!(
 
 /// CPU exception.
 pub type CpuException = Exception;
+
+/// The shift of `sstatus.UXL`, which controls the XLEN of the user space.
+const SSTATUS_UXL_SHIFT: usize = 32;
+const SSTATUS_UXL_MASK: usize = 0b11;
+const UXL_32: usize = 1;
+const UXL_64: usize = 2;
+
+static IS_COMPAT_MODE_SUPPORTED: AtomicBool = AtomicBool::new(false);
+
+/// Returns whether the CPU can run 32-bit programs in the user space.
+pub fn is_compat_mode_supported() -> bool {
+    IS_COMPAT_MODE_SUPPORTED.load(Ordering::Relaxed)
+}
+
+/// Detects whether the user space can run in the 32-bit mode.
+///
+/// `sstatus.UXL` is a WARL field. It is read-only if the CPU supports only one XLEN for the
+/// user space, in which case writing 32 to the field takes no effect.
+pub(in crate::arch) fn init_compat_mode() {
+    let new_sstatus: usize;
+    // SAFETY: Changing `sstatus.UXL` does not affect the kernel, and the old value is restored
+    // immediately.
+    unsafe {
+        asm!(
+            "csrr {old}, sstatus",
+            "and {tmp}, {old}, {clear}",
+            "or {tmp}, {tmp}, {set}",
+            "csrw sstatus, {tmp}",
+            "csrr {new}, sstatus",
+            "csrw sstatus, {old}",
+            old = out(reg) _,
+            new = out(reg) new_sstatus,
+            tmp = out(reg) _,
+            clear = in(reg) !(SSTATUS_UXL_MASK << SSTATUS_UXL_SHIFT),
+            set = in(reg) UXL_32 << SSTATUS_UXL_SHIFT,
+            options(nostack)
+        );
+    }
+
+    let is_supported = (new_sstatus >> SSTATUS_UXL_SHIFT) & SSTATUS_UXL_MASK == UXL_32;
+    IS_COMPAT_MODE_SUPPORTED.store(is_supported, Ordering::Relaxed);
+    log::info!("32-bit user space supported: {}", is_supported);
+}
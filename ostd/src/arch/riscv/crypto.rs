@@ -0,0 +1,194 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The scalar cryptography extensions (Zkn).
+//!
+//! The Zkne and Zknd extensions provide instructions for the rounds of AES encryption and
+//! decryption, and the Zknh extension provides instructions for the `Σ` and `σ` functions of
+//! SHA-2. The instructions are exposed as methods of zero-sized tokens (e.g., [`Zkne`]),
+//! which can only be obtained if the extensions are available on all harts, so that the
+//! methods are safe to call.
+//!
+//! Only the instructions of RV64 are supported.
+
+use crate::cpu::{cpu_features, CpuFeatures};
+
+/// A token that proves the availability of the Zkne extension (i.e., AES encryption).
+#[derive(Debug, Clone, Copy)]
+pub struct Zkne(());
+
+/// A token that proves the availability of the Zknd extension (i.e., AES decryption).
+#[derive(Debug, Clone, Copy)]
+pub struct Zknd(());
+
+/// A token that proves the availability of the Zknh extension (i.e., SHA-2).
+#[derive(Debug, Clone, Copy)]
+pub struct Zknh(());
+
+/// Returns a token if the Zkne extension is available.
+pub fn zkne() -> Option<Zkne> {
+    cpu_features()
+        .contains(CpuFeatures::AES_ENC)
+        .then_some(Zkne(()))
+}
+
+/// Returns a token if the Zknd extension is available.
+pub fn zknd() -> Option<Zknd> {
+    cpu_features()
+        .contains(CpuFeatures::AES_DEC)
+        .then_some(Zknd(()))
+}
+
+/// Returns a token if the Zknh extension is available.
+pub fn zknh() -> Option<Zknh> {
+    cpu_features()
+        .contains(CpuFeatures::SHA2)
+        .then_some(Zknh(()))
+}
+
+// The instructions are encoded with `.insn` so that the assembler does not need to know about
+// the extensions.
+
+macro_rules! r_type_insn {
+    ($(#[$attr:meta])* $name:ident, $funct7:literal) => {
+        $(#[$attr])*
+        #[inline]
+        pub fn $name(self, rs1: u64, rs2: u64) -> u64 {
+            let rd: u64;
+            // SAFETY: The token proves that the instruction is available, and the
+            // instruction only computes on the registers.
+            unsafe {
+                core::arch::asm!(
+                    concat!(".insn r 0x33, 0, ", $funct7, ", {rd}, {rs1}, {rs2}"),
+                    rd = lateout(reg) rd,
+                    rs1 = in(reg) rs1,
+                    rs2 = in(reg) rs2,
+                    options(pure, nomem, nostack, preserves_flags)
+                );
+            }
+            rd
+        }
+    };
+}
+
+macro_rules! i_type_insn {
+    ($(#[$attr:meta])* $name:ident, $imm:literal) => {
+        $(#[$attr])*
+        #[inline]
+        pub fn $name(self, rs1: u64) -> u64 {
+            let rd: u64;
+            // SAFETY: The token proves that the instruction is available, and the
+            // instruction only computes on the registers.
+            unsafe {
+                core::arch::asm!(
+                    concat!(".insn i 0x13, 1, {rd}, {rs1}, ", $imm),
+                    rd = lateout(reg) rd,
+                    rs1 = in(reg) rs1,
+                    options(pure, nomem, nostack, preserves_flags)
+                );
+            }
+            rd
+        }
+    };
+}
+
+/// Defines an instruction that computes on the low 32 bits of the register.
+macro_rules! i_type_insn_32 {
+    ($(#[$attr:meta])* $name:ident, $imm:literal) => {
+        $(#[$attr])*
+        #[inline]
+        pub fn $name(self, rs1: u32) -> u32 {
+            let rd: u64;
+            // SAFETY: The token proves that the instruction is available, and the
+            // instruction only computes on the registers.
+            unsafe {
+                core::arch::asm!(
+                    concat!(".insn i 0x13, 1, {rd}, {rs1}, ", $imm),
+                    rd = lateout(reg) rd,
+                    rs1 = in(reg) rs1 as u64,
+                    options(pure, nomem, nostack, preserves_flags)
+                );
+            }
+            // The result is sign-extended to 64 bits.
+            rd as u32
+        }
+    };
+}
+
+impl Zkne {
+    r_type_insn!(
+        /// Performs `ShiftRows` and `SubBytes` on the state (`rs2` as the high half and `rs1`
+        /// as the low half), and returns the low half (`aes64es`).
+        aes64es,
+        "0x19"
+    );
+    r_type_insn!(
+        /// Performs `ShiftRows`, `SubBytes`, and `MixColumns` on the state, and returns the
+        /// low half (`aes64esm`).
+        aes64esm,
+        "0x1b"
+    );
+}
+
+impl Zknd {
+    r_type_insn!(
+        /// Performs `InvShiftRows` and `InvSubBytes` on the state (`rs2` as the high half and
+        /// `rs1` as the low half), and returns the low half (`aes64ds`).
+        aes64ds,
+        "0x1d"
+    );
+    r_type_insn!(
+        /// Performs `InvShiftRows`, `InvSubBytes`, and `InvMixColumns` on the state, and
+        /// returns the low half (`aes64dsm`).
+        aes64dsm,
+        "0x1f"
+    );
+    i_type_insn!(
+        /// Performs `InvMixColumns` on two columns, which converts round keys for the
+        /// equivalent inverse cipher (`aes64im`).
+        aes64im,
+        "0x300"
+    );
+}
+
+impl Zknh {
+    i_type_insn_32!(
+        /// Computes `Σ0` of SHA-256 (`sha256sum0`).
+        sha256sum0,
+        "0x100"
+    );
+    i_type_insn_32!(
+        /// Computes `Σ1` of SHA-256 (`sha256sum1`).
+        sha256sum1,
+        "0x101"
+    );
+    i_type_insn_32!(
+        /// Computes `σ0` of SHA-256 (`sha256sig0`).
+        sha256sig0,
+        "0x102"
+    );
+    i_type_insn_32!(
+        /// Computes `σ1` of SHA-256 (`sha256sig1`).
+        sha256sig1,
+        "0x103"
+    );
+    i_type_insn!(
+        /// Computes `Σ0` of SHA-512 (`sha512sum0`).
+        sha512sum0,
+        "0x104"
+    );
+    i_type_insn!(
+        /// Computes `Σ1` of SHA-512 (`sha512sum1`).
+        sha512sum1,
+        "0x105"
+    );
+    i_type_insn!(
+        /// Computes `σ0` of SHA-512 (`sha512sig0`).
+        sha512sig0,
+        "0x106"
+    );
+    i_type_insn!(
+        /// Computes `σ1` of SHA-512 (`sha512sig1`).
+        sha512sig1,
+        "0x107"
+    );
+}
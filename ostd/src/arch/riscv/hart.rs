@@ -0,0 +1,118 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The identities of the harts.
+//!
+//! The hart IDs are not necessarily contiguous or zero-based, so the harts are numbered with the
+//! logical CPU IDs. The boot hart is always the CPU 0, and the other harts that are usable are
+//! numbered in the order of the device tree (or the MADT, if ACPI is used).
+
+use spin::Once;
+
+use crate::{
+    arch::{acpi, boot::DEVICE_TREE},
+    cpu::CpuId,
+};
+
+/// The maximum number of the harts that are used as CPUs.
+const MAX_NR_CPUS: usize = 256;
+
+/// The hart IDs of the CPUs, indexed by the CPU IDs.
+struct HartIds {
+    ids: [usize; MAX_NR_CPUS],
+    len: usize,
+}
+
+impl HartIds {
+    fn push(&mut self, hart_id: usize) {
+        if self.ids[..self.len].contains(&hart_id) {
+            return;
+        }
+        if self.len == MAX_NR_CPUS {
+            log::warn!("Hart {} is not used: too many harts", hart_id);
+            return;
+        }
+        self.ids[self.len] = hart_id;
+        self.len += 1;
+    }
+}
+
+static HART_IDS: Once<HartIds> = Once::new();
+
+/// Builds the mapping between the hart IDs and the CPU IDs.
+///
+/// This should be called in the boot context after the device tree and the ACPI tables are
+/// parsed, and before the number of the CPUs is counted.
+pub(in crate::arch) fn init(boot_hart_id: usize) {
+    let mut hart_ids = HartIds {
+        ids: [0; MAX_NR_CPUS],
+        len: 0,
+    };
+    hart_ids.push(boot_hart_id);
+
+    if acpi::is_enabled() {
+        for hart in acpi::madt::harts() {
+            hart_ids.push(hart.hart_id);
+        }
+    } else {
+        let device_tree = DEVICE_TREE.get().unwrap();
+        let cpus = device_tree
+            .find_node("/cpus")
+            .into_iter()
+            .flat_map(|_| device_tree.cpus());
+        for cpu in cpus {
+            // The harts that are disabled (e.g., the monitor cores of some SoCs) are not used.
+            let status = cpu.property("status").and_then(|prop| prop.as_str());
+            if !matches!(status, None | Some("okay") | Some("ok")) {
+                continue;
+            }
+            hart_ids.push(cpu.ids().first());
+        }
+    }
+
+    HART_IDS.call_once(|| hart_ids);
+}
+
+/// Returns the number of the harts that are used as CPUs.
+pub(in crate::arch) fn nr_harts() -> usize {
+    HART_IDS.get().unwrap().len
+}
+
+/// The IDs of the implementation of a hart, as in the `mvendorid`, `marchid` and `mimpid`
+/// CSRs.
+#[derive(Debug, Clone, Copy)]
+pub struct MachineIds {
+    /// The JEDEC manufacturer ID of the vendor.
+    pub mvendorid: usize,
+    /// The microarchitecture ID.
+    pub marchid: usize,
+    /// The implementation version.
+    pub mimpid: usize,
+}
+
+/// Returns the hart ID of the CPU.
+pub fn hart_id(cpu: CpuId) -> usize {
+    let hart_ids = HART_IDS.get().unwrap();
+    hart_ids.ids[..hart_ids.len][cpu.as_usize()]
+}
+
+/// Returns the CPU whose hart ID is `hart_id`.
+///
+/// If the hart is not used as a CPU, `None` is returned.
+pub fn cpu_id(hart_id: usize) -> Option<CpuId> {
+    let hart_ids = HART_IDS.get().unwrap();
+    let index = hart_ids.ids[..hart_ids.len]
+        .iter()
+        .position(|id| *id == hart_id)?;
+    Some(CpuId::try_from(index).unwrap())
+}
+
+/// Returns the IDs of the implementation of the current hart.
+///
+/// The machine-mode CSRs are read by the SBI, which returns zeros if they are not implemented.
+pub fn machine_ids() -> MachineIds {
+    MachineIds {
+        mvendorid: sbi_rt::get_mvendorid(),
+        marchid: sbi_rt::get_marchid(),
+        mimpid: sbi_rt::get_mimpid(),
+    }
+}
@@ -0,0 +1,294 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Interrupts.
+
+pub(super) mod plic;
+
+use alloc::{boxed::Box, fmt::Debug, sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use id_alloc::IdAlloc;
+use spin::Once;
+
+use crate::{
+    arch::hart,
+    cpu::{CpuId, CpuSet},
+    cpu_local,
+    sync::{LocalIrqDisabled, Mutex, PreemptDisabled, SpinLock, SpinLockGuard},
+    trap::{call_irq_callback_functions, TrapFrame},
+    Error,
+};
+
+/// The global allocator for software defined IRQ lines.
+pub(crate) static IRQ_ALLOCATOR: Once<SpinLock<IdAlloc>> = Once::new();
+
+pub(crate) static IRQ_LIST: Once<Vec<IrqLine>> = Once::new();
+
+pub(crate) fn init() {
+    let mut list: Vec<IrqLine> = Vec::new();
+    for i in 0..256 {
+        list.push(IrqLine {
+            irq_num: i as u8,
+            callback_list: SpinLock::new(Vec::new()),
+            state: SpinLock::new(LineState {
+                nr_callbacks: 0,
+                nr_masks: 0,
+                affinity: None,
+            }),
+        });
+    }
+    IRQ_LIST.call_once(|| list);
+    CALLBACK_ID_ALLOCATOR.call_once(|| Mutex::new(IdAlloc::with_capacity(256)));
+    IRQ_ALLOCATOR.call_once(|| SpinLock::new(IdAlloc::with_capacity(256)));
+
+    init_on_ap();
+}
+
+/// Enables the IPIs on the current CPU.
+pub(super) fn init_on_ap() {
+    // SAFETY: The IPIs are dispatched to the IRQ lines by `handle_software_interrupt`.
+    unsafe { riscv::register::sie::set_ssoft() };
+}
+
+/// Restores the PLIC sources of the IRQ lines after the system resumes from suspend-to-RAM.
+pub(super) fn resume() {
+    plic::resume();
+    for line in IRQ_LIST.get().unwrap() {
+        line.state.lock().sync_source(line.irq_num);
+    }
+}
+
+pub(crate) fn enable_local() {
+    unsafe { riscv::interrupt::enable() }
+}
+
+pub(crate) fn disable_local() {
+    riscv::interrupt::disable();
+}
+
+pub(crate) fn is_local_enabled() -> bool {
+    riscv::register::sstatus::read().sie()
+}
+
+static CALLBACK_ID_ALLOCATOR: Once<Mutex<IdAlloc>> = Once::new();
+
+pub struct CallbackElement {
+    function: Box<dyn Fn(&TrapFrame) + Send + Sync + 'static>,
+    id: usize,
+}
+
+impl CallbackElement {
+    pub fn call(&self, element: &TrapFrame) {
+        self.function.call((element,));
+    }
+}
+
+impl Debug for CallbackElement {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("CallbackElement")
+            .field("id", &self.id)
+            .finish()
+    }
+}
+
+/// An interrupt request (IRQ) line.
+#[derive(Debug)]
+pub(crate) struct IrqLine {
+    pub(crate) irq_num: u8,
+    pub(crate) callback_list: SpinLock<Vec<CallbackElement>>,
+    /// The state of the PLIC source of the IRQ line.
+    ///
+    /// It is not protected by `callback_list`, since the line may be masked by the callbacks.
+    state: SpinLock<LineState, LocalIrqDisabled>,
+}
+
+#[derive(Debug)]
+struct LineState {
+    nr_callbacks: usize,
+    nr_masks: usize,
+    /// The CPUs to which the interrupts are delivered, or `None` for all the CPUs.
+    affinity: Option<CpuSet>,
+}
+
+impl LineState {
+    /// Enables the PLIC source if the line has callbacks and is not masked.
+    fn sync_source(&self, irq_num: u8) {
+        let is_enabled = self.nr_callbacks > 0 && self.nr_masks == 0;
+        plic::set_enabled(irq_num, is_enabled, self.affinity.as_ref());
+    }
+}
+
+impl IrqLine {
+    /// Acquire an interrupt request line.
+    ///
+    /// # Safety
+    ///
+    /// This function is marked unsafe as manipulating interrupt lines is
+    /// considered a dangerous operation.
+    #[expect(clippy::redundant_allocation)]
+    pub unsafe fn acquire(irq_num: u8) -> Arc<&'static Self> {
+        Arc::new(IRQ_LIST.get().unwrap().get(irq_num as usize).unwrap())
+    }
+
+    /// Get the IRQ number.
+    pub fn num(&self) -> u8 {
+        self.irq_num
+    }
+
+    pub fn callback_list(
+        &self,
+    ) -> SpinLockGuard<alloc::vec::Vec<CallbackElement>, PreemptDisabled> {
+        self.callback_list.lock()
+    }
+
+    /// Masks the IRQ line, so that its interrupts are not delivered until it is unmasked.
+    ///
+    /// The masks are counted, so the line is unmasked after all the masks are undone.
+    pub fn mask(&self) {
+        let mut state = self.state.lock();
+        state.nr_masks += 1;
+        state.sync_source(self.irq_num);
+    }
+
+    /// Unmasks the IRQ line.
+    pub fn unmask(&self) {
+        let mut state = self.state.lock();
+        state.nr_masks = state.nr_masks.checked_sub(1).unwrap();
+        state.sync_source(self.irq_num);
+    }
+
+    /// Returns the CPUs to which the interrupts are delivered.
+    pub fn affinity(&self) -> CpuSet {
+        self.state
+            .lock()
+            .affinity
+            .clone()
+            .unwrap_or_else(CpuSet::new_full)
+    }
+
+    /// Sets the CPUs to which the interrupts are delivered.
+    ///
+    /// The interrupts can only be steered if the IRQ line is a source of the PLIC, and all the
+    /// CPUs in the set have contexts in the PLIC.
+    pub fn set_affinity(&self, cpus: &CpuSet) -> Result<(), Error> {
+        if !plic::can_deliver_to(self.irq_num, cpus) {
+            return Err(Error::InvalidArgs);
+        }
+        let mut state = self.state.lock();
+        state.affinity = Some(cpus.clone());
+        state.sync_source(self.irq_num);
+        Ok(())
+    }
+
+    /// Register a callback that will be invoked when the IRQ is active.
+    ///
+    /// A handle to the callback is returned. Dropping the handle
+    /// automatically unregisters the callback.
+    ///
+    /// For each IRQ line, multiple callbacks may be registered.
+    pub fn on_active<F>(&self, callback: F) -> IrqCallbackHandle
+    where
+        F: Fn(&TrapFrame) + Sync + Send + 'static,
+    {
+        let allocate_id = CALLBACK_ID_ALLOCATOR.get().unwrap().lock().alloc().unwrap();
+        self.callback_list.lock().push(CallbackElement {
+            function: Box::new(callback),
+            id: allocate_id,
+        });
+
+        let mut state = self.state.lock();
+        state.nr_callbacks += 1;
+        state.sync_source(self.irq_num);
+        drop(state);
+
+        IrqCallbackHandle {
+            irq_num: self.irq_num,
+            id: allocate_id,
+        }
+    }
+}
+
+/// The handle to a registered callback for a IRQ line.
+///
+/// When the handle is dropped, the callback will be unregistered automatically.
+#[must_use]
+#[derive(Debug)]
+pub struct IrqCallbackHandle {
+    irq_num: u8,
+    id: usize,
+}
+
+impl Drop for IrqCallbackHandle {
+    fn drop(&mut self) {
+        let irq_line = IRQ_LIST.get().unwrap().get(self.irq_num as usize).unwrap();
+        let mut a = irq_line.callback_list.lock();
+        a.retain(|item| item.id != self.id);
+        drop(a);
+        CALLBACK_ID_ALLOCATOR.get().unwrap().lock().free(self.id);
+
+        let mut state = irq_line.state.lock();
+        state.nr_callbacks -= 1;
+        state.sync_source(self.irq_num);
+    }
+}
+
+/// Dispatches the external interrupts claimed from the PLIC to the IRQ lines.
+pub(crate) fn handle_external_interrupt(trap_frame: &TrapFrame) {
+    // The local IRQs are disabled, so the CPU cannot change.
+    let cpu = crate::cpu::current_cpu_racy();
+    while let Some(source) = plic::claim(cpu) {
+        match u8::try_from(source) {
+            // The interrupt is completed by `interrupts_ack`.
+            Ok(irq_num) => call_irq_callback_functions(trap_frame, irq_num as usize),
+            Err(_) => plic::complete(cpu, source),
+        }
+    }
+}
+
+/// Completes the handling of the interrupt of the IRQ line on the current CPU.
+pub(crate) fn complete(irq_num: usize) {
+    plic::complete(crate::cpu::current_cpu_racy(), irq_num as u32);
+}
+
+/// Sends a general inter-processor interrupt (IPI) to the specified CPU.
+///
+/// # Safety
+///
+/// The caller must ensure that the CPU ID and the interrupt number corresponds
+/// to a safe function to call.
+pub(crate) unsafe fn send_ipi(cpu_id: CpuId, irq_num: u8) {
+    PENDING_IPIS.get_on_cpu(cpu_id)[irq_num as usize / 64]
+        .fetch_or(1 << (irq_num % 64), Ordering::Release);
+
+    let hart_mask = sbi_rt::HartMask::from_mask_base(1, hart::hart_id(cpu_id));
+    if let Err(err) = sbi_rt::send_ipi(hart_mask).into_result() {
+        log::warn!(
+            "Failed to send an IPI to CPU {}: {:?}",
+            cpu_id.as_usize(),
+            err
+        );
+    }
+}
+
+cpu_local! {
+    /// The bitmap of the IRQ lines, whose IPIs are sent to the CPU but are not handled.
+    static PENDING_IPIS: [AtomicU64; 4] = [const { AtomicU64::new(0) }; 4];
+}
+
+/// Dispatches the IPIs, which are the supervisor software interrupts, to the IRQ lines.
+pub(crate) fn handle_software_interrupt(trap_frame: &TrapFrame) {
+    // The pending bit is cleared before the bitmap is read, so no IPI is missed.
+    // SAFETY: Clearing the pending bit only acknowledges the interrupt.
+    unsafe { riscv::register::sip::clear_ssoft() };
+
+    // The local IRQs are disabled, so the CPU cannot change.
+    let cpu = crate::cpu::current_cpu_racy();
+    for (index, pending) in PENDING_IPIS.get_on_cpu(cpu).iter().enumerate() {
+        let mut bits = pending.swap(0, Ordering::Acquire);
+        while bits != 0 {
+            let irq_num = index * 64 + bits.trailing_zeros() as usize;
+            bits &= bits - 1;
+            call_irq_callback_functions(trap_frame, irq_num);
+        }
+    }
+}
@@ -0,0 +1,291 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The platform-level interrupt controller (PLIC).
+//!
+//! The PLIC routes the interrupts of the devices to the harts. Each hart has a context for the
+//! supervisor mode, which has its own enable bits, priority threshold, and claim/complete
+//! register. An interrupt is only delivered to the harts whose contexts enable it, so the
+//! affinity of an IRQ line is controlled by steering its enable bit among the contexts.
+//!
+//! The IRQ lines are identified with the interrupt sources of the PLIC. Since the IRQ numbers
+//! are of `u8`, only the sources below 256 are supported.
+//!
+//! Reference: <https://github.com/riscv/riscv-plic-spec/blob/master/riscv-plic.adoc>
+
+use spin::Once;
+
+use crate::{
+    arch::{acpi, boot::DEVICE_TREE, hart},
+    boot::param::{register_param, BootParam},
+    cpu::{all_cpus, CpuId, CpuSet},
+    io::MmioRegion,
+    mm::{HasPaddr, VmIoOnce},
+    prelude::*,
+    sync::{LocalIrqDisabled, SpinLock},
+};
+
+/// The interrupt number of the supervisor external interrupts, to which the supervisor-mode
+/// contexts are connected.
+const IRQ_S_EXT: u32 = 9;
+
+const PRIORITY_BASE: usize = 0;
+const ENABLE_BASE: usize = 0x2000;
+const ENABLE_STRIDE: usize = 0x80;
+const CONTEXT_BASE: usize = 0x20_0000;
+const CONTEXT_STRIDE: usize = 0x1000;
+const CONTEXT_THRESHOLD: usize = 0;
+const CONTEXT_CLAIM: usize = 4;
+
+/// The priority of the enabled sources, which is above the threshold of all the contexts.
+///
+/// It can be set by `plic.max_priority=` in the kernel command line, e.g., if the PLIC is shared
+/// with the firmware that uses lower priorities. It must not exceed the maximum priority that the
+/// PLIC implements.
+static MAX_PRIORITY: BootParam<u32> = BootParam::new("plic", "max_priority", 1);
+
+struct Plic {
+    io_mem: MmioRegion,
+    /// The number of the sources, excluding the source zero that does not exist.
+    nr_sources: usize,
+    /// The supervisor-mode contexts of the CPUs, indexed by the CPU IDs.
+    contexts: Vec<Option<usize>>,
+    /// The lock that serializes the updates of the enable bits.
+    enable_lock: SpinLock<(), LocalIrqDisabled>,
+}
+
+static PLIC: Once<Plic> = Once::new();
+
+/// Detects the PLIC from the device tree (or the MADT if ACPI is used), and enables the
+/// supervisor external interrupts.
+///
+/// All the sources are disabled until their IRQ lines have callbacks.
+pub(in crate::arch) fn init() {
+    register_param(&MAX_PRIORITY);
+
+    let found = if acpi::is_enabled() {
+        find_in_madt()
+    } else {
+        find_in_device_tree()
+    };
+    let Some((io_mem, nr_sources, hart_contexts)) = found else {
+        return;
+    };
+    let base = io_mem.paddr();
+    let nr_sources = nr_sources.min(u8::MAX as usize);
+
+    let contexts = all_cpus()
+        .map(|cpu| {
+            let hart_id = hart::hart_id(cpu);
+            hart_contexts
+                .iter()
+                .find(|(hart, _)| *hart == hart_id)
+                .map(|(_, context)| *context)
+        })
+        .collect();
+
+    let plic = PLIC.call_once(|| Plic {
+        io_mem,
+        nr_sources,
+        contexts,
+        enable_lock: SpinLock::new(()),
+    });
+    plic.reset_contexts();
+
+    log::info!(
+        "PLIC at {:#x}, {} sources, contexts: {:?}",
+        base,
+        nr_sources,
+        plic.contexts
+    );
+}
+
+/// Returns the MMIO region, the number of the sources, and the pairs of the hart IDs and their
+/// supervisor-mode contexts of the PLIC in the device tree.
+fn find_in_device_tree() -> Option<(MmioRegion, usize, Vec<(usize, usize)>)> {
+    let device_tree = DEVICE_TREE.get().unwrap();
+    let node = device_tree.all_nodes().find(|node| {
+        node.compatible().is_some_and(|compatible| {
+            compatible
+                .all()
+                .any(|name| name == "riscv,plic0" || name == "sifive,plic-1.0.0")
+        })
+    })?;
+    let Ok(io_mem) = MmioRegion::claim_fdt_reg(&node) else {
+        log::warn!("The MMIO region of the PLIC is not available");
+        return None;
+    };
+    let nr_sources = node
+        .property("riscv,ndev")
+        .and_then(|prop| prop.as_usize())
+        .unwrap_or(0);
+
+    // Each entry of `interrupts-extended` is a pair of the phandle of the interrupt controller
+    // of a hart and the interrupt number, and the index of the entry is the context.
+    let hart_contexts = node
+        .property("interrupts-extended")
+        .map(|prop| {
+            prop.value
+                .chunks_exact(8)
+                .enumerate()
+                .filter_map(|(context, entry)| {
+                    let phandle = u32::from_be_bytes(entry[0..4].try_into().unwrap());
+                    let irq = u32::from_be_bytes(entry[4..8].try_into().unwrap());
+                    if irq != IRQ_S_EXT {
+                        return None;
+                    }
+                    Some((phandle_to_hart(phandle)?, context))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Some((io_mem, nr_sources, hart_contexts))
+}
+
+/// Returns the same as [`find_in_device_tree`], but for the first PLIC in the MADT.
+///
+/// The supervisor-mode context of a hart is in the external interrupt controller ID of its RINTC.
+fn find_in_madt() -> Option<(MmioRegion, usize, Vec<(usize, usize)>)> {
+    let plic = acpi::madt::plic()?;
+    let Ok(io_mem) = MmioRegion::claim(plic.base..plic.base + plic.size, "plic") else {
+        log::warn!("The MMIO region of the PLIC is not available");
+        return None;
+    };
+
+    let hart_contexts = acpi::madt::harts()
+        .filter(|hart| (hart.ext_intc_id >> 24) as u8 == plic.id)
+        .map(|hart| (hart.hart_id, (hart.ext_intc_id & 0xffff) as usize))
+        .collect();
+
+    Some((io_mem, plic.nr_sources, hart_contexts))
+}
+
+/// Resets the PLIC after the system resumes from suspend-to-RAM, which loses its state.
+///
+/// All the sources are disabled until the IRQ lines re-enable them.
+pub(super) fn resume() {
+    if let Some(plic) = PLIC.get() {
+        plic.reset_contexts();
+    }
+}
+
+/// Returns the hart ID whose interrupt controller has the phandle.
+fn phandle_to_hart(phandle: u32) -> Option<usize> {
+    DEVICE_TREE
+        .get()
+        .unwrap()
+        .all_nodes()
+        .filter(|node| node.name.starts_with("cpu@"))
+        .find(|cpu| {
+            cpu.children().any(|child| {
+                child
+                    .property("phandle")
+                    .and_then(|prop| prop.as_usize())
+                    .is_some_and(|value| value == phandle as usize)
+            })
+        })
+        .and_then(|cpu| cpu.reg()?.next())
+        .map(|reg| reg.starting_address as usize)
+}
+
+fn enable_offset(context: usize, word: usize) -> usize {
+    ENABLE_BASE + context * ENABLE_STRIDE + word * 4
+}
+
+fn context_offset(context: usize) -> usize {
+    CONTEXT_BASE + context * CONTEXT_STRIDE
+}
+
+impl Plic {
+    fn read(&self, offset: usize) -> u32 {
+        self.io_mem.read_once::<u32>(offset).unwrap()
+    }
+
+    fn write(&self, offset: usize, value: u32) {
+        self.io_mem.write_once(offset, &value).unwrap()
+    }
+
+    fn has_source(&self, source: u8) -> bool {
+        source != 0 && source as usize <= self.nr_sources
+    }
+
+    /// Disables all the sources in the contexts, and enables the supervisor external interrupts.
+    fn reset_contexts(&self) {
+        for context in self.contexts.iter().flatten() {
+            for word in 0..=self.nr_sources / 32 {
+                self.write(enable_offset(*context, word), 0);
+            }
+            self.write(context_offset(*context) + CONTEXT_THRESHOLD, 0);
+        }
+
+        // SAFETY: The sources are all disabled, and the interrupts are dispatched to the IRQ
+        // lines by `handle_external_interrupt`.
+        unsafe { riscv::register::sie::set_sext() };
+    }
+}
+
+/// Enables or disables the source.
+///
+/// An enabled source is enabled in the contexts of the CPUs in the set (or all the CPUs if the
+/// set is `None`), and is disabled in the others.
+pub(super) fn set_enabled(source: u8, is_enabled: bool, cpus: Option<&CpuSet>) {
+    let Some(plic) = PLIC.get().filter(|plic| plic.has_source(source)) else {
+        return;
+    };
+
+    if is_enabled {
+        // A zero priority never interrupts, since it is not above any threshold.
+        let priority = MAX_PRIORITY.get().max(1);
+        plic.write(PRIORITY_BASE + source as usize * 4, priority);
+    }
+
+    let word = source as usize / 32;
+    let bit = 1 << (source % 32);
+    let _guard = plic.enable_lock.lock();
+    for (cpu, context) in plic.contexts.iter().enumerate() {
+        let Some(context) = context else {
+            continue;
+        };
+        let cpu = CpuId::try_from(cpu).unwrap();
+        let offset = enable_offset(*context, word);
+        let value = plic.read(offset);
+        if is_enabled && cpus.is_none_or(|cpus| cpus.contains(cpu)) {
+            plic.write(offset, value | bit);
+        } else {
+            plic.write(offset, value & !bit);
+        }
+    }
+}
+
+/// Returns whether the source can be delivered to all the CPUs in the set.
+pub(super) fn can_deliver_to(source: u8, cpus: &CpuSet) -> bool {
+    let Some(plic) = PLIC.get().filter(|plic| plic.has_source(source)) else {
+        return false;
+    };
+    cpus.iter().all(|cpu| {
+        plic.contexts
+            .get(cpu.as_usize())
+            .is_some_and(Option::is_some)
+    })
+}
+
+/// Claims the pending interrupt with the highest priority on the current CPU.
+///
+/// It returns `None` if there are no pending interrupts.
+pub(super) fn claim(cpu: CpuId) -> Option<u32> {
+    let plic = PLIC.get()?;
+    let context = plic.contexts.get(cpu.as_usize()).copied().flatten()?;
+    let source = plic.read(context_offset(context) + CONTEXT_CLAIM);
+    (source != 0).then_some(source)
+}
+
+/// Completes the handling of the claimed interrupt on the current CPU.
+pub(super) fn complete(cpu: CpuId, source: u32) {
+    let Some(plic) = PLIC.get() else {
+        return;
+    };
+    let Some(context) = plic.contexts.get(cpu.as_usize()).copied().flatten() else {
+        return;
+    };
+    plic.write(context_offset(context) + CONTEXT_CLAIM, source);
+}
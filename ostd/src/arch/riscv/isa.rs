@@ -0,0 +1,165 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The extensions of the RISC-V ISA.
+//!
+//! The extensions are detected during the boot, and the features that are relevant to the
+//! kernel (e.g., the F and D extensions, or the Zicbom extension) are recorded in
+//! [`CpuFeatures`].
+//!
+//! The single-letter extensions are reported to the user space as a bitmap, in which the bit `n` is set if the
+//! extension named by the `n`-th letter of the alphabet is available, as `AT_HWCAP` of Linux.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{
+    arch::{acpi, boot::DEVICE_TREE},
+    cpu::{features, CpuFeatures},
+};
+
+/// Returns the bit of the single-letter extension in the bitmap.
+const fn isa_bit(letter: u8) -> u64 {
+    1 << (letter - b'a')
+}
+
+/// The extensions that can be reported to the user space.
+///
+/// The V extension is not reported because the vector registers are not saved on context
+/// switches.
+const USER_VISIBLE_EXTENSIONS: u64 =
+    isa_bit(b'i') | isa_bit(b'm') | isa_bit(b'a') | isa_bit(b'f') | isa_bit(b'd') | isa_bit(b'c');
+
+static HWCAP: AtomicU64 = AtomicU64::new(0);
+
+/// The extensions that are available to the kernel, including those hidden from the user space.
+static EXTENSIONS: AtomicU64 = AtomicU64::new(0);
+
+/// Detects the extensions from the device tree (or the RHCT if ACPI is used) and the SBI extensions by probing the firmware.
+///
+/// The detected features are recorded in [`CpuFeatures`], which are queried by the other
+/// architecture-specific code.
+pub(in crate::arch) fn init() {
+    let mut extensions = 0;
+    let mut features = probe_sbi_extensions();
+
+    let mut add_extension = |ext: &[u8]| match ext {
+        [letter] => extensions |= letter_to_bits(*letter),
+        _ => features |= named_extension_to_features(ext),
+    };
+    let mut add_isa_string = |isa: &str| {
+        // The ISA string looks like "rv64imafdc_zicsr_zifencei", where the single-letter
+        // extensions are before the first underscore.
+        let mut parts = isa.split('_');
+        let letters = parts.next().unwrap_or("");
+        let letters = letters
+            .strip_prefix("rv64")
+            .or_else(|| letters.strip_prefix("rv32"))
+            .unwrap_or("");
+        for letter in letters.bytes() {
+            add_extension(&[letter]);
+        }
+        parts.for_each(|ext| add_extension(ext.as_bytes()));
+    };
+
+    if acpi::is_enabled() {
+        if let Some(isa) = acpi::rhct::isa_string() {
+            add_isa_string(isa);
+        }
+    } else if let Some(cpu) = DEVICE_TREE.get().unwrap().cpus().next() {
+        if let Some(isa) = cpu.property("riscv,isa").and_then(|prop| prop.as_str()) {
+            add_isa_string(isa);
+        }
+
+        if let Some(prop) = cpu.property("riscv,isa-extensions") {
+            prop.value
+                .split(|b| *b == 0)
+                .filter(|ext| !ext.is_empty())
+                .for_each(&mut add_extension);
+        }
+    }
+
+    // The F extension is not usable without the D extension, since the kernel saves the
+    // floating-point registers as double-precision ones.
+    if extensions & isa_bit(b'd') == 0 {
+        extensions &= !isa_bit(b'f');
+    }
+
+    if extensions & isa_bit(b'f') != 0 {
+        features |= CpuFeatures::FPU;
+    }
+    if extensions & isa_bit(b'v') != 0 {
+        features |= CpuFeatures::VECTOR;
+    }
+    if extensions & isa_bit(b'h') != 0 {
+        features |= CpuFeatures::HYPERVISOR;
+    }
+
+    EXTENSIONS.store(extensions, Ordering::Relaxed);
+    HWCAP.store(extensions & USER_VISIBLE_EXTENSIONS, Ordering::Relaxed);
+    features::init(features);
+
+    log::info!("ISA extensions: {:#x}", HWCAP.load(Ordering::Relaxed));
+}
+
+/// Returns the features provided by the multi-letter extension (e.g., "zicbom").
+fn named_extension_to_features(ext: &[u8]) -> CpuFeatures {
+    match ext.to_ascii_lowercase().as_slice() {
+        b"zicbom" => CpuFeatures::CACHE_BLOCK_MGMT,
+        b"svpbmt" => CpuFeatures::PAGE_MEM_TYPES,
+        b"zkne" => CpuFeatures::AES_ENC,
+        b"zknd" => CpuFeatures::AES_DEC,
+        b"zknh" => CpuFeatures::SHA2,
+        // Zkn is the shorthand for Zbkb, Zbkc, Zbkx, Zkne, Zknd, and Zknh, and Zk includes Zkn.
+        b"zkn" | b"zk" => CpuFeatures::AES_ENC | CpuFeatures::AES_DEC | CpuFeatures::SHA2,
+        _ => CpuFeatures::empty(),
+    }
+}
+
+/// Probes the SBI extensions that are implemented by the firmware.
+fn probe_sbi_extensions() -> CpuFeatures {
+    let mut features = CpuFeatures::empty();
+    features.set(
+        CpuFeatures::SBI_TIMER,
+        sbi_rt::probe_extension(sbi_rt::Timer).is_available(),
+    );
+    features.set(
+        CpuFeatures::SBI_IPI,
+        sbi_rt::probe_extension(sbi_rt::Ipi).is_available(),
+    );
+    features.set(
+        CpuFeatures::SBI_RFENCE,
+        sbi_rt::probe_extension(sbi_rt::Fence).is_available(),
+    );
+    features.set(
+        CpuFeatures::SBI_HSM,
+        sbi_rt::probe_extension(sbi_rt::Hsm).is_available(),
+    );
+    features.set(
+        CpuFeatures::SBI_PMU,
+        sbi_rt::probe_extension(sbi_rt::Pmu).is_available(),
+    );
+    features.set(
+        CpuFeatures::SBI_SUSP,
+        sbi_rt::probe_extension(sbi_rt::Suspend).is_available(),
+    );
+    features
+}
+
+/// Returns the bits of the single-letter extension, where "g" is the shorthand for "imafd".
+fn letter_to_bits(letter: u8) -> u64 {
+    match letter.to_ascii_lowercase() {
+        b'g' => isa_bit(b'i') | isa_bit(b'm') | isa_bit(b'a') | isa_bit(b'f') | isa_bit(b'd'),
+        letter @ b'a'..=b'z' => isa_bit(letter),
+        _ => 0,
+    }
+}
+
+/// Returns the bitmap of the single-letter extensions that are available to the user space.
+pub fn hwcap() -> u64 {
+    HWCAP.load(Ordering::Relaxed)
+}
+
+/// Returns whether the single-letter extension (e.g., `b'h'` for the hypervisor extension) is
+/// available to the kernel.
+pub fn has_extension(letter: u8) -> bool {
+    letter.is_ascii_lowercase() && EXTENSIONS.load(Ordering::Relaxed) & isa_bit(letter) != 0
+}
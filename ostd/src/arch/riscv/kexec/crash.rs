@@ -0,0 +1,402 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The crash kernel region and the memory of the crashed kernel.
+//!
+//! If the kernel command line contains `crashkernel=SIZE[@OFFSET]`, a region of `SIZE` bytes
+//! is reserved at boot (at `OFFSET` if specified, or at the highest possible address
+//! otherwise) to hold the crash image, which will not be overwritten by the current kernel.
+//!
+//! If the current kernel is a capture kernel, i.e., it is booted by a crash image, the memory
+//! of the crashed kernel is described by an ELF core header, which is passed by the
+//! `linux,elfcorehdr` property of `/chosen` or `elfcorehdr=[SIZE@]OFFSET` in the kernel
+//! command line. The memory that can be used by the capture kernel is limited by the
+//! `linux,usable-memory-range` property of `/chosen`, and the rest of the memory is reserved,
+//! so that the memory of the crashed kernel can be read by [`read_old_memory`].
+
+use core::{mem::size_of, ops::Range};
+
+use align_ext::AlignExt;
+use ostd_pod::Pod;
+use spin::Once;
+
+use crate::{
+    arch::boot::DEVICE_TREE,
+    boot::{
+        memory_region::{MemoryRegionArray, MemoryRegionType},
+        EARLY_INFO,
+    },
+    mm::{paddr_to_vaddr, Fallible, FallibleVmRead, VmReader, VmWriter, PAGE_SIZE},
+    prelude::*,
+    Error,
+};
+
+/// The alignment of the crash kernel region.
+///
+/// The RISC-V kernel images should be loaded at 2 MiB-aligned addresses.
+pub const CRASH_KERNEL_ALIGN: usize = 2 * 1024 * 1024;
+
+static CRASH_KERNEL_REGION: Once<Range<Paddr>> = Once::new();
+static ELF_CORE_HEADER: Once<Range<Paddr>> = Once::new();
+/// The physical memory ranges of the segments described by the ELF core header
+static OLD_MEMORY: Once<Vec<Range<Paddr>>> = Once::new();
+
+/// Returns the crash kernel region, if reserved.
+pub fn crash_kernel_region() -> Option<Range<Paddr>> {
+    CRASH_KERNEL_REGION.get().cloned()
+}
+
+/// Returns the ELF core header of the crashed kernel, if the current kernel is a capture
+/// kernel.
+///
+/// The header describes the segments of the crashed kernel, where the file offsets
+/// (`p_offset`) of the segments are their physical addresses.
+pub fn elf_core_header() -> Option<&'static [u8]> {
+    // The header is valid only if its segments have been checked.
+    OLD_MEMORY.get()?;
+    let header = ELF_CORE_HEADER.get()?;
+
+    // SAFETY: The header is in a reserved region (see `parse_elf_core_header`), which is
+    // linearly mapped and is never written.
+    Some(unsafe {
+        core::slice::from_raw_parts(paddr_to_vaddr(header.start) as *const u8, header.len())
+    })
+}
+
+/// Reads the memory of the crashed kernel at `paddr` to `writer`.
+///
+/// The memory must be in one of the segments described by the ELF core header. The reading
+/// stops at the end of the segment. Returns the number of bytes read.
+pub fn read_old_memory(paddr: Paddr, writer: &mut VmWriter<Fallible>) -> Result<usize> {
+    let segment = OLD_MEMORY
+        .get()
+        .and_then(|segments| segments.iter().find(|segment| segment.contains(&paddr)))
+        .ok_or(Error::InvalidArgs)?;
+    let len = (segment.end - paddr).min(writer.avail());
+
+    // SAFETY: The segments are checked to be in the reserved regions, which is linearly mapped
+    // and not used by the current kernel.
+    let mut reader =
+        unsafe { VmReader::from_kernel_space(paddr_to_vaddr(paddr) as *const u8, len) };
+    reader.read_fallible(writer).map_err(|(err, _)| err)
+}
+
+/// Reserves the crash kernel region as specified by the kernel command line.
+///
+/// The region must be in the usable memory and must not overlap the other regions in
+/// `regions` or the ranges in `busy`.
+pub(in crate::arch) fn reserve_crash_kernel(
+    cmdline: &str,
+    regions: &MemoryRegionArray,
+    busy: &[Range<Paddr>],
+) -> Option<Range<Paddr>> {
+    let arg = cmdline
+        .split(' ')
+        .find_map(|arg| arg.strip_prefix("crashkernel="))?;
+    let (size, offset) = match arg.split_once('@') {
+        Some((size, offset)) => (parse_size(size)?, Some(parse_size(offset)?)),
+        None => (parse_size(arg)?, None),
+    };
+    let size = size.align_up(PAGE_SIZE);
+    if size == 0 {
+        return None;
+    }
+
+    // Returns the start of the lowest region that overlaps the range.
+    let find_conflict = |range: &Range<Paddr>| {
+        regions
+            .iter()
+            .filter(|region| region.typ() != MemoryRegionType::Usable)
+            .map(|region| region.base()..region.end())
+            .chain(busy.iter().cloned())
+            .filter(|other| other.start < range.end && range.start < other.end)
+            .map(|other| other.start)
+            .min()
+    };
+    let usable_regions = || {
+        regions
+            .iter()
+            .filter(|region| region.typ() == MemoryRegionType::Usable)
+    };
+
+    let region = if let Some(offset) = offset {
+        let range = offset..offset.checked_add(size)?;
+        let is_valid = offset % CRASH_KERNEL_ALIGN == 0
+            && usable_regions()
+                .any(|region| region.base() <= range.start && range.end <= region.end())
+            && find_conflict(&range).is_none();
+        is_valid.then_some(range)
+    } else {
+        // Search from the top of each usable region.
+        usable_regions()
+            .filter_map(|region| {
+                let mut end = region.end();
+                loop {
+                    let start = end.checked_sub(size)?.align_down(CRASH_KERNEL_ALIGN);
+                    if start < region.base() {
+                        return None;
+                    }
+                    let range = start..start + size;
+                    match find_conflict(&range) {
+                        Some(conflict) => end = conflict,
+                        None => return Some(range),
+                    }
+                }
+            })
+            .max_by_key(|range| range.start)
+    };
+
+    let Some(region) = region else {
+        crate::early_println!("Failed to reserve the crash kernel region for `{}`", arg);
+        return None;
+    };
+    Some(CRASH_KERNEL_REGION.call_once(|| region).clone())
+}
+
+/// Finds the ELF core header if the current kernel is a capture kernel.
+///
+/// The header should be reserved by the caller.
+pub(in crate::arch) fn parse_elf_core_header(cmdline: &str) -> Option<Range<Paddr>> {
+    let (start, size) = if let Some(value) = chosen_property("linux,elfcorehdr") {
+        read_reg(value)?
+    } else {
+        let arg = cmdline
+            .split(' ')
+            .find_map(|arg| arg.strip_prefix("elfcorehdr="))?;
+        match arg.split_once('@') {
+            Some((size, offset)) => (parse_size(offset)?, parse_size(size)?),
+            None => {
+                let start = parse_size(arg)?;
+                // SAFETY: The physical memory is linearly mapped during boot. The header is
+                // passed by the crashed kernel, which puts it in the memory.
+                let ehdr = unsafe { (paddr_to_vaddr(start) as *const Elf64Ehdr).read_unaligned() };
+                let size = ehdr.e_phoff as usize + ehdr.e_phnum as usize * size_of::<Elf64Phdr>();
+                (start, size)
+            }
+        }
+    };
+
+    let header = start.align_down(PAGE_SIZE)..start.checked_add(size)?.align_up(PAGE_SIZE);
+    ELF_CORE_HEADER.call_once(|| start..start + size);
+    Some(header)
+}
+
+/// Returns the memory range that can be used by the capture kernel, if limited.
+pub(in crate::arch) fn parse_usable_memory_range() -> Option<Range<Paddr>> {
+    let (start, size) = read_reg(chosen_property("linux,usable-memory-range")?)?;
+    Some(start..start.checked_add(size)?)
+}
+
+/// Checks the segments described by the ELF core header.
+pub(super) fn init() {
+    let Some(header) = ELF_CORE_HEADER.get() else {
+        return;
+    };
+    // SAFETY: The header is in a reserved region, which is linearly mapped and never written.
+    let header = unsafe {
+        core::slice::from_raw_parts(paddr_to_vaddr(header.start) as *const u8, header.len())
+    };
+
+    let Some(ehdr) = header
+        .get(..size_of::<Elf64Ehdr>())
+        .map(Elf64Ehdr::from_bytes)
+    else {
+        log::warn!("The ELF core header is too short");
+        return;
+    };
+    if ehdr.e_ident[..4] != *b"\x7fELF" || ehdr.e_phentsize as usize != size_of::<Elf64Phdr>() {
+        log::warn!("The ELF core header is invalid");
+        return;
+    }
+
+    let regions = &EARLY_INFO.get().unwrap().memory_regions;
+    let max_paddr = regions.iter().map(|region| region.end()).max().unwrap();
+    // The memory of the crashed kernel must not be used by the current kernel.
+    let is_reserved = |range: &Range<Paddr>| {
+        range.end <= max_paddr
+            && regions.iter().all(|region| {
+                !matches!(
+                    region.typ(),
+                    MemoryRegionType::Usable
+                        | MemoryRegionType::Kernel
+                        | MemoryRegionType::Module
+                        | MemoryRegionType::Reclaimable
+                        | MemoryRegionType::Framebuffer
+                ) || region.end() <= range.start
+                    || range.end <= region.base()
+            })
+    };
+
+    let mut segments = Vec::new();
+    for i in 0..ehdr.e_phnum as usize {
+        let offset = ehdr.e_phoff as usize + i * size_of::<Elf64Phdr>();
+        let Some(phdr) = header
+            .get(offset..offset + size_of::<Elf64Phdr>())
+            .map(Elf64Phdr::from_bytes)
+        else {
+            log::warn!("The ELF core header is truncated");
+            return;
+        };
+        if (phdr.p_type != PT_LOAD && phdr.p_type != PT_NOTE) || phdr.p_filesz == 0 {
+            continue;
+        }
+
+        let start = phdr.p_offset as usize;
+        let Some(segment) = start
+            .checked_add(phdr.p_filesz as usize)
+            .map(|end| start..end)
+            .filter(|segment| is_reserved(segment))
+        else {
+            log::warn!(
+                "The segment at {:#x} of the crashed kernel is used by the current kernel",
+                start
+            );
+            return;
+        };
+        segments.push(segment);
+    }
+
+    log::info!("Found {} segments of the crashed kernel", segments.len());
+    OLD_MEMORY.call_once(|| segments);
+}
+
+/// Builds an ELF core header that describes the memory of the current kernel.
+///
+/// The header is passed to the capture kernel by a crash image, which is loaded by the
+/// kernel itself (rather than by the user space). The memory includes all the RAM that is used
+/// by the current kernel, which excludes the crash kernel region.
+///
+/// No notes (e.g., the registers of the harts) are recorded in the header.
+pub fn build_elf_core_header() -> Vec<u8> {
+    let regions = &EARLY_INFO.get().unwrap().memory_regions;
+
+    let mut segments: Vec<Range<Paddr>> = Vec::new();
+    for region in regions.iter() {
+        if !matches!(
+            region.typ(),
+            MemoryRegionType::Usable
+                | MemoryRegionType::Kernel
+                | MemoryRegionType::Module
+                | MemoryRegionType::Reclaimable
+        ) {
+            continue;
+        }
+        match segments.last_mut() {
+            Some(last) if last.end == region.base() => last.end = region.end(),
+            _ => segments.push(region.base()..region.end()),
+        }
+    }
+
+    let ehdr = Elf64Ehdr {
+        e_ident: *b"\x7fELF\x02\x01\x01\0\0\0\0\0\0\0\0\0",
+        e_type: ET_CORE,
+        e_machine: EM_RISCV,
+        e_version: 1,
+        e_entry: 0,
+        e_phoff: size_of::<Elf64Ehdr>() as u64,
+        e_shoff: 0,
+        e_flags: 0,
+        e_ehsize: size_of::<Elf64Ehdr>() as u16,
+        e_phentsize: size_of::<Elf64Phdr>() as u16,
+        e_phnum: segments.len() as u16,
+        e_shentsize: 0,
+        e_shnum: 0,
+        e_shstrndx: 0,
+    };
+
+    let mut header = Vec::from(ehdr.as_bytes());
+    for segment in segments {
+        let phdr = Elf64Phdr {
+            p_type: PT_LOAD,
+            p_flags: PF_R | PF_W | PF_X,
+            // Like Linux, the offsets in the header passed to the capture kernel are the
+            // physical addresses.
+            p_offset: segment.start as u64,
+            p_vaddr: paddr_to_vaddr(segment.start) as u64,
+            p_paddr: segment.start as u64,
+            p_filesz: segment.len() as u64,
+            p_memsz: segment.len() as u64,
+            p_align: 0,
+        };
+        header.extend_from_slice(phdr.as_bytes());
+    }
+    header
+}
+
+/// The type of ELF core files.
+pub const ET_CORE: u16 = 4;
+/// The machine type of RISC-V.
+pub const EM_RISCV: u16 = 243;
+/// The type of loadable segments.
+pub const PT_LOAD: u32 = 1;
+/// The type of note segments.
+pub const PT_NOTE: u32 = 4;
+
+const PF_X: u32 = 0x1;
+const PF_W: u32 = 0x2;
+const PF_R: u32 = 0x4;
+
+/// The ELF header of 64-bit ELF files.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+#[expect(missing_docs)]
+pub struct Elf64Ehdr {
+    pub e_ident: [u8; 16],
+    pub e_type: u16,
+    pub e_machine: u16,
+    pub e_version: u32,
+    pub e_entry: u64,
+    pub e_phoff: u64,
+    pub e_shoff: u64,
+    pub e_flags: u32,
+    pub e_ehsize: u16,
+    pub e_phentsize: u16,
+    pub e_phnum: u16,
+    pub e_shentsize: u16,
+    pub e_shnum: u16,
+    pub e_shstrndx: u16,
+}
+
+/// The program header of 64-bit ELF files.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+#[expect(missing_docs)]
+pub struct Elf64Phdr {
+    pub p_type: u32,
+    pub p_flags: u32,
+    pub p_offset: u64,
+    pub p_vaddr: u64,
+    pub p_paddr: u64,
+    pub p_filesz: u64,
+    pub p_memsz: u64,
+    pub p_align: u64,
+}
+
+fn chosen_property(name: &str) -> Option<&'static [u8]> {
+    let chosen = DEVICE_TREE.get().unwrap().find_node("/chosen")?;
+    Some(chosen.property(name)?.value)
+}
+
+/// Reads the address and the size in a `reg`-like property.
+///
+/// Both `#address-cells` and `#size-cells` are assumed to be 2, as on all RV64 platforms.
+fn read_reg(value: &[u8]) -> Option<(usize, usize)> {
+    let start = u64::from_be_bytes(value.get(0..8)?.try_into().unwrap());
+    let size = u64::from_be_bytes(value.get(8..16)?.try_into().unwrap());
+    Some((start as usize, size as usize))
+}
+
+/// Parses a size in the kernel command line, e.g., `256M` or `0x80000000`.
+fn parse_size(arg: &str) -> Option<usize> {
+    let (digits, shift) = match arg.as_bytes().last()? {
+        b'K' | b'k' => (&arg[..arg.len() - 1], 10),
+        b'M' | b'm' => (&arg[..arg.len() - 1], 20),
+        b'G' | b'g' => (&arg[..arg.len() - 1], 30),
+        _ => (arg, 0),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        usize::from_str_radix(hex, 16).ok()?
+    } else {
+        digits.parse::<usize>().ok()?
+    };
+    value.checked_mul(1 << shift)
+}
@@ -0,0 +1,147 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Building the device tree for the new kernel.
+//!
+//! The new kernel is booted on the same platform, so its device tree is copied from the
+//! current one (see [`DEVICE_TREE`]), except that the properties in `/chosen` (e.g., the
+//! kernel command line and the initramfs) are replaced.
+
+use alloc::{collections::BTreeMap, string::String};
+
+use fdt::node::FdtNode;
+
+use crate::{arch::boot::DEVICE_TREE, prelude::*};
+
+pub(in crate::arch) const FDT_MAGIC: u32 = 0xd00d_feed;
+pub(in crate::arch) const FDT_VERSION: u32 = 17;
+pub(in crate::arch) const FDT_LAST_COMP_VERSION: u32 = 16;
+pub(in crate::arch) const FDT_HEADER_SIZE: usize = 40;
+
+pub(in crate::arch) const FDT_BEGIN_NODE: u32 = 0x1;
+pub(in crate::arch) const FDT_END_NODE: u32 = 0x2;
+pub(in crate::arch) const FDT_PROP: u32 = 0x3;
+pub(in crate::arch) const FDT_END: u32 = 0x9;
+
+/// Builds a device tree for the new kernel from the current device tree.
+///
+/// Each entry of `chosen` sets (if the value is `Some`) or removes (if the value is `None`)
+/// the property of the name in `/chosen`. The other nodes and properties are copied.
+pub fn build_device_tree(chosen: &[(&str, Option<&[u8]>)]) -> Vec<u8> {
+    let device_tree = DEVICE_TREE.get().unwrap();
+
+    let mut builder = FdtBuilder::default();
+    builder.node(device_tree.find_node("/").unwrap(), 0, chosen);
+    builder.token(FDT_END);
+
+    let mut reservations = Vec::new();
+    for reservation in device_tree.memory_reservations() {
+        reservations.extend_from_slice(&(reservation.address() as u64).to_be_bytes());
+        reservations.extend_from_slice(&(reservation.size() as u64).to_be_bytes());
+    }
+    reservations.extend_from_slice(&[0u8; 16]);
+
+    let off_mem_rsvmap = FDT_HEADER_SIZE;
+    let off_dt_struct = off_mem_rsvmap + reservations.len();
+    let off_dt_strings = off_dt_struct + builder.structure.len();
+    let total_size = off_dt_strings + builder.strings.len();
+
+    let mut blob = Vec::with_capacity(total_size);
+    for field in [
+        FDT_MAGIC,
+        total_size as u32,
+        off_dt_struct as u32,
+        off_dt_strings as u32,
+        off_mem_rsvmap as u32,
+        FDT_VERSION,
+        FDT_LAST_COMP_VERSION,
+        // The boot CPU is not used by RISC-V kernels, which get the hart ID from `a0`.
+        0,
+        builder.strings.len() as u32,
+        builder.structure.len() as u32,
+    ] {
+        blob.extend_from_slice(&field.to_be_bytes());
+    }
+    blob.extend_from_slice(&reservations);
+    blob.extend_from_slice(&builder.structure);
+    blob.extend_from_slice(&builder.strings);
+    blob
+}
+
+#[derive(Default)]
+struct FdtBuilder {
+    structure: Vec<u8>,
+    strings: Vec<u8>,
+    string_offsets: BTreeMap<String, u32>,
+}
+
+impl FdtBuilder {
+    fn node(&mut self, node: FdtNode, depth: usize, chosen: &[(&str, Option<&[u8]>)]) {
+        // The name of the root node is empty in the blob.
+        let name = if depth == 0 { "" } else { node.name };
+        let is_chosen = depth == 1 && name == "chosen";
+
+        self.token(FDT_BEGIN_NODE);
+        self.bytes(&[name.as_bytes(), b"\0"].concat());
+
+        for property in node.properties() {
+            if is_chosen && chosen.iter().any(|(name, _)| *name == property.name) {
+                continue;
+            }
+            self.property(property.name, property.value);
+        }
+        if is_chosen {
+            self.chosen_properties(chosen);
+        }
+
+        for child in node.children() {
+            self.node(child, depth + 1, chosen);
+        }
+        if depth == 0 && !node.children().any(|child| child.name == "chosen") {
+            self.token(FDT_BEGIN_NODE);
+            self.bytes(b"chosen\0");
+            self.chosen_properties(chosen);
+            self.token(FDT_END_NODE);
+        }
+
+        self.token(FDT_END_NODE);
+    }
+
+    fn chosen_properties(&mut self, chosen: &[(&str, Option<&[u8]>)]) {
+        for (name, value) in chosen {
+            if let Some(value) = value {
+                self.property(name, value);
+            }
+        }
+    }
+
+    fn property(&mut self, name: &str, value: &[u8]) {
+        let name_offset = self.string_offset(name);
+        self.token(FDT_PROP);
+        self.structure
+            .extend_from_slice(&(value.len() as u32).to_be_bytes());
+        self.structure.extend_from_slice(&name_offset.to_be_bytes());
+        self.bytes(value);
+    }
+
+    fn string_offset(&mut self, name: &str) -> u32 {
+        if let Some(offset) = self.string_offsets.get(name) {
+            return *offset;
+        }
+        let offset = self.strings.len() as u32;
+        self.strings.extend_from_slice(name.as_bytes());
+        self.strings.push(0);
+        self.string_offsets.insert(String::from(name), offset);
+        offset
+    }
+
+    fn token(&mut self, token: u32) {
+        self.structure.extend_from_slice(&token.to_be_bytes());
+    }
+
+    /// Appends the bytes to the structure block, padded to 4 bytes.
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.structure.extend_from_slice(bytes);
+        let padded_len = self.structure.len().next_multiple_of(4);
+        self.structure.resize(padded_len, 0);
+    }
+}
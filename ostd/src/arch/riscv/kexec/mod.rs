@@ -0,0 +1,370 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Kexec, i.e., booting a new kernel from the current kernel without the firmware.
+//!
+//! A [`KexecImage`] stages the segments of the new kernel (the kernel image, the initramfs,
+//! the device tree, etc.) in the memory. Executing the image disables the MMU on the current
+//! hart, copies the segments to their destinations, and jumps to the entry of the new kernel
+//! with the same arguments as passed from SBI (i.e., `a0 = hart ID` and `a1 = device tree`).
+//!
+//! There are two kinds of images:
+//!  - The normal images, which are executed on demand (e.g., by `reboot`) to fast-reboot the
+//!    system. Their segments are copied to free frames when loaded, and relocated to their
+//!    destinations, which may overlap the current kernel, right before the jump.
+//!  - The crash images, which are executed if the current kernel panics, so that the new
+//!    kernel (the capture kernel) can export the memory of the crashed kernel (see
+//!    [`crash`]). Their segments are loaded directly into the reserved crash kernel region,
+//!    so no relocation is needed when the current kernel is no longer reliable.
+
+pub mod crash;
+pub mod fdt;
+
+use core::{arch::global_asm, mem::size_of, ops::Range};
+
+use align_ext::AlignExt;
+use ostd_pod::Pod;
+
+use crate::{
+    arch::boot::BOOT_HART_ID,
+    boot::{memory_region::MemoryRegionType, EARLY_INFO},
+    mm::{
+        paddr_to_vaddr, Fallible, FallibleVmRead, Frame, FrameAllocOptions, Segment, VmReader,
+        VmWriter, PAGE_SIZE,
+    },
+    prelude::*,
+    sync::SpinLock,
+    trap::disable_local,
+    Error,
+};
+
+global_asm!(include_str!("relocate.S"));
+
+extern "C" {
+    fn __kexec_relocate_start();
+    fn __kexec_relocate_end();
+    fn __kexec_jump(
+        hart_id: usize,
+        device_tree: Paddr,
+        entry: Paddr,
+        entries: Paddr,
+        nr_entries: usize,
+        control_page: Paddr,
+    ) -> !;
+}
+
+/// The maximum number of segments in an image.
+pub const KEXEC_SEGMENT_MAX: usize = 16;
+
+/// The magic number at the beginning of a flattened device tree.
+const FDT_MAGIC: u32 = 0xd00d_feed;
+
+/// An entry of the relocation list, which is read by the relocation code.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct RelocEntry {
+    src: Paddr,
+    dst: Paddr,
+    len: usize,
+}
+
+/// An image of a new kernel to be executed by kexec.
+#[derive(Debug)]
+pub struct KexecImage {
+    entry: Paddr,
+    is_crash: bool,
+    /// The destination ranges of the segments
+    segments: Vec<Range<Paddr>>,
+    /// The physical address of the device tree, which is found in the segments
+    device_tree: Option<Paddr>,
+    /// The frames that hold the data of the segments and their destinations
+    ///
+    /// This is always empty for crash images.
+    pages: Vec<(Frame<()>, Paddr)>,
+    /// The frames that are allocated but cannot be used since they overlap the destinations
+    ///
+    /// They are kept until all the frames are allocated, so that they are not allocated
+    /// again, and are freed when the image is prepared.
+    rejected: Vec<Frame<()>>,
+    /// The control page and the relocation list, which are allocated when the image is set
+    prepared: Option<(Frame<()>, Segment<()>)>,
+}
+
+impl KexecImage {
+    /// Creates an image whose segments will be loaded at `segments` and whose entry is
+    /// `entry`.
+    ///
+    /// The destinations must be page-aligned, must not overlap each other, and must be in the
+    /// RAM that is not reserved (or in the crash kernel region for crash images). The entry
+    /// must be in one of the destinations.
+    ///
+    /// Since the segments of crash images are loaded directly into the crash kernel region,
+    /// creating a crash image unloads the current crash image.
+    pub fn new(entry: Paddr, segments: &[Range<Paddr>], is_crash: bool) -> Result<Self> {
+        if segments.is_empty() || segments.len() > KEXEC_SEGMENT_MAX {
+            return Err(Error::InvalidArgs);
+        }
+
+        for (i, segment) in segments.iter().enumerate() {
+            if segment.is_empty()
+                || segment.start % PAGE_SIZE != 0
+                || segment.end % PAGE_SIZE != 0
+                || segments[..i]
+                    .iter()
+                    .any(|other| other.start < segment.end && segment.start < other.end)
+            {
+                return Err(Error::InvalidArgs);
+            }
+
+            let is_valid = if is_crash {
+                crash::crash_kernel_region().is_some_and(|region| {
+                    region.start <= segment.start && segment.end <= region.end
+                })
+            } else {
+                is_ram(segment)
+            };
+            if !is_valid {
+                return Err(Error::AccessDenied);
+            }
+        }
+
+        if !segments.iter().any(|segment| segment.contains(&entry)) {
+            return Err(Error::InvalidArgs);
+        }
+
+        if is_crash {
+            CRASH_IMAGE.lock().take();
+        }
+
+        Ok(Self {
+            entry,
+            is_crash,
+            segments: segments.to_vec(),
+            device_tree: None,
+            pages: Vec::new(),
+            rejected: Vec::new(),
+            prepared: None,
+        })
+    }
+
+    /// Returns whether the image is executed when the kernel panics.
+    pub fn is_crash(&self) -> bool {
+        self.is_crash
+    }
+
+    /// Loads the data of the `index`-th segment from `reader`.
+    ///
+    /// The data must not be longer than the segment. The rest of the segment is filled with
+    /// zeros. If the data starts with the magic number of the device tree, the segment is
+    /// passed to the new kernel as the device tree.
+    pub fn load_segment(&mut self, index: usize, reader: &mut VmReader<Fallible>) -> Result<()> {
+        let segment = self.segments.get(index).ok_or(Error::InvalidArgs)?.clone();
+        if reader.remain() > segment.len() {
+            return Err(Error::InvalidArgs);
+        }
+
+        let mut magic = [0u8; size_of::<u32>()];
+        if reader.remain() >= magic.len() {
+            reader
+                .clone()
+                .read_fallible(&mut VmWriter::from(&mut magic[..]))
+                .map_err(|(err, _)| err)?;
+            if u32::from_be_bytes(magic) == FDT_MAGIC {
+                self.device_tree = Some(segment.start);
+            }
+        }
+
+        if self.is_crash {
+            // SAFETY: The crash kernel region is reserved (so it is not used by the current
+            // kernel) and linearly mapped. The segment is in the region.
+            let mut writer = unsafe {
+                VmWriter::from_kernel_space(paddr_to_vaddr(segment.start) as *mut u8, segment.len())
+            };
+            reader.read_fallible(&mut writer).map_err(|(err, _)| err)?;
+            writer
+                .to_fallible()
+                .fill_zeros(usize::MAX)
+                .map_err(|(err, _)| err)?;
+            return Ok(());
+        }
+
+        for dst in segment.step_by(PAGE_SIZE) {
+            let frame = self.alloc_frame()?;
+            reader
+                .read_fallible(&mut frame.writer())
+                .map_err(|(err, _)| err)?;
+            self.pages.push((frame, dst));
+        }
+        Ok(())
+    }
+
+    /// Executes the image.
+    ///
+    /// This disables the MMU, relocates the segments (which may overwrite the current kernel),
+    /// and jumps to the new kernel. The image must have been prepared by [`set_image`].
+    ///
+    /// The caller should quiesce the devices before executing the image, since they may keep
+    /// accessing the memory that is being overwritten.
+    pub fn execute(self) -> ! {
+        let _irq_guard = disable_local();
+
+        // Only the boot hart is running, since the application processors are not brought up
+        // yet (see `boot::smp`). So the current hart is already the only one that runs.
+        let hart_id = *BOOT_HART_ID.get().unwrap();
+        let (control_page, entries) = self.prepared.as_ref().unwrap();
+
+        // SAFETY: The image has been prepared, so the control page contains the relocation
+        // code and the relocation list describes the pages of the image, none of which
+        // overlaps the destinations. No code of the current kernel runs after the jump.
+        unsafe {
+            __kexec_jump(
+                hart_id,
+                self.device_tree.unwrap(),
+                self.entry,
+                entries.start_paddr(),
+                self.pages.len(),
+                control_page.start_paddr(),
+            )
+        }
+    }
+
+    /// Allocates a frame that does not overlap the destinations.
+    fn alloc_frame(&mut self) -> Result<Frame<()>> {
+        loop {
+            let frame = FrameAllocOptions::new().alloc_frame()?;
+            if self.overlaps_segments(&(frame.start_paddr()..frame.start_paddr() + PAGE_SIZE)) {
+                self.rejected.push(frame);
+                continue;
+            }
+            return Ok(frame);
+        }
+    }
+
+    /// Allocates the control page and the relocation list.
+    fn prepare(&mut self) -> Result<()> {
+        let control_page = self.alloc_frame()?;
+        let code_len = __kexec_relocate_end as usize - __kexec_relocate_start as usize;
+        // SAFETY: The relocation code is in the kernel text, which is always readable.
+        let mut code =
+            unsafe { VmReader::from_kernel_space(__kexec_relocate_start as *const u8, code_len) };
+        control_page.writer().write(&mut code);
+
+        let nr_list_pages = (self.pages.len() * size_of::<RelocEntry>())
+            .align_up(PAGE_SIZE)
+            .max(PAGE_SIZE)
+            / PAGE_SIZE;
+        let entries = loop {
+            let segment = FrameAllocOptions::new().alloc_segment(nr_list_pages)?;
+            if !self.overlaps_segments(&(segment.start_paddr()..segment.end_paddr())) {
+                break segment;
+            }
+            // The frames of the segment are freed after all the frames are allocated.
+            self.rejected.extend(segment);
+        };
+
+        let mut writer = entries.writer();
+        for (frame, dst) in self.pages.iter() {
+            writer.write_val(&RelocEntry {
+                src: frame.start_paddr(),
+                dst: *dst,
+                len: PAGE_SIZE,
+            })?;
+        }
+
+        self.rejected.clear();
+        self.prepared = Some((control_page, entries));
+        Ok(())
+    }
+
+    fn overlaps_segments(&self, range: &Range<Paddr>) -> bool {
+        self.segments
+            .iter()
+            .any(|segment| segment.start < range.end && range.start < segment.end)
+    }
+}
+
+static KEXEC_IMAGE: SpinLock<Option<KexecImage>> = SpinLock::new(None);
+static CRASH_IMAGE: SpinLock<Option<KexecImage>> = SpinLock::new(None);
+
+/// Sets the normal image or the crash image, depending on [`KexecImage::is_crash`].
+///
+/// The image is prepared to be executed, which fails if no segment contains a device tree.
+pub fn set_image(mut image: KexecImage) -> Result<()> {
+    if image.device_tree.is_none() {
+        return Err(Error::InvalidArgs);
+    }
+    image.prepare()?;
+
+    if image.is_crash {
+        *CRASH_IMAGE.lock() = Some(image);
+    } else {
+        *KEXEC_IMAGE.lock() = Some(image);
+    }
+    Ok(())
+}
+
+/// Unloads the normal image or the crash image.
+pub fn unload_image(is_crash: bool) {
+    if is_crash {
+        CRASH_IMAGE.lock().take();
+    } else {
+        KEXEC_IMAGE.lock().take();
+    }
+}
+
+/// Returns whether the normal image or the crash image is loaded.
+pub fn is_image_loaded(is_crash: bool) -> bool {
+    if is_crash {
+        CRASH_IMAGE.lock().is_some()
+    } else {
+        KEXEC_IMAGE.lock().is_some()
+    }
+}
+
+/// Takes the normal image, which is executed by [`KexecImage::execute`].
+pub fn take_image() -> Option<KexecImage> {
+    KEXEC_IMAGE.lock().take()
+}
+
+/// Executes the crash image if it is loaded.
+///
+/// This should be called when the kernel panics. It returns if no crash image is loaded.
+/// Since the lock may be held by the panicked code, the image is not executed if the lock is
+/// contended.
+pub fn crash_kexec() {
+    let Some(mut image) = CRASH_IMAGE.try_lock() else {
+        return;
+    };
+    if let Some(image) = image.take() {
+        image.execute();
+    }
+}
+
+/// Returns whether the range is in the RAM that can be overwritten by a new kernel.
+fn is_ram(range: &Range<Paddr>) -> bool {
+    let regions = &EARLY_INFO.get().unwrap().memory_regions;
+
+    // The regions do not overlap each other, so the range is in them if the sum of the
+    // lengths of the intersections equals the length of the range.
+    let len_in_ram: usize = regions
+        .iter()
+        .filter(|region| {
+            matches!(
+                region.typ(),
+                MemoryRegionType::Usable
+                    | MemoryRegionType::Kernel
+                    | MemoryRegionType::Module
+                    | MemoryRegionType::Reclaimable
+            )
+        })
+        .map(|region| {
+            let start = region.base().max(range.start);
+            let end = region.end().min(range.end);
+            end.saturating_sub(start)
+        })
+        .sum();
+    len_in_ram == range.len()
+}
+
+pub(in crate::arch) fn init() {
+    crash::init();
+}
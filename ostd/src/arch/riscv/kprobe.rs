@@ -0,0 +1,414 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Kernel probes (kprobes), i.e., the breakpoints inserted into the kernel code at runtime.
+//!
+//! Registering a [`Kprobe`] replaces the instruction at the probed address with an `ebreak`
+//! (or a `c.ebreak` if the instruction is compressed or not 4-byte aligned). When the
+//! breakpoint is hit, the pre-handler is called with the trap frame, and then the displaced
+//! instruction is executed out of line in a slot, which is followed by another `ebreak`. When
+//! the second breakpoint is hit, the post-handler is called, and the execution continues
+//! after the probed instruction. The local IRQs are disabled while stepping the displaced
+//! instruction, so at most one instruction is being stepped on each CPU.
+//!
+//! The instructions whose effects depend on their addresses (e.g., `auipc`, jumps and
+//! branches) and the system instructions (e.g., `ecall` and CSR accesses) cannot be executed
+//! out of line, so they cannot be probed. Neither can the code used to handle the
+//! breakpoints (e.g., the spin locks).
+//!
+//! A handler should not hit any probes. If a probe is hit while a handler is running on the
+//! same CPU, the probe is disarmed to avoid the infinite recursion, and the hit is counted as
+//! missed.
+
+use core::{
+    arch::global_asm,
+    cell::RefCell,
+    sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+};
+
+use crate::{
+    arch::mm::flush_icache_all,
+    cpu::all_cpus,
+    cpu_local,
+    prelude::*,
+    sync::{LocalIrqDisabled, SpinLock},
+    trap::{disable_local, TrapFrame},
+    Error,
+};
+
+/// The number of the slots to execute the displaced instructions out of line.
+const NR_XOL_SLOTS: usize = 64;
+
+/// The size of each slot, which holds the displaced instruction and an `ebreak`.
+const XOL_SLOT_SIZE: usize = 8;
+
+const EBREAK: u32 = 0x0010_0073;
+const C_EBREAK: u16 = 0x9002;
+
+// The slots reside in the kernel code, which is mapped as executable and writable.
+global_asm!(
+    ".pushsection .text.kprobe_xol, \"ax\"",
+    ".balign 8",
+    ".global __kprobe_xol_slots",
+    "__kprobe_xol_slots:",
+    ".space {size}",
+    ".popsection",
+    size = const NR_XOL_SLOTS * XOL_SLOT_SIZE,
+);
+
+extern "C" {
+    fn __kprobe_xol_slots();
+    fn __executable_start();
+    fn __etext();
+}
+
+/// The handler of a kprobe, which can inspect and modify the registers.
+pub type KprobeHandler = Box<dyn Fn(&mut TrapFrame) + Send + Sync>;
+
+/// A kernel probe at an instruction.
+pub struct Kprobe {
+    addr: Vaddr,
+    pre_handler: Option<KprobeHandler>,
+    post_handler: Option<KprobeHandler>,
+}
+
+impl Kprobe {
+    /// Creates a probe at the address without any handlers.
+    pub fn new(addr: Vaddr) -> Self {
+        Self {
+            addr,
+            pre_handler: None,
+            post_handler: None,
+        }
+    }
+
+    /// Creates a probe at the offset of the kernel symbol without any handlers.
+    ///
+    /// Returns `None` if there is no such symbol (see [`crate::kallsyms`]).
+    pub fn at_symbol(name: &str, offset: usize) -> Option<Self> {
+        let addr = crate::kallsyms::addr_of_symbol(name)?;
+        Some(Self::new(addr.checked_add(offset)?))
+    }
+
+    /// Sets the handler called before the probed instruction is executed.
+    pub fn pre_handler<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&mut TrapFrame) + Send + Sync + 'static,
+    {
+        self.pre_handler = Some(Box::new(handler));
+        self
+    }
+
+    /// Sets the handler called after the probed instruction is executed.
+    pub fn post_handler<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&mut TrapFrame) + Send + Sync + 'static,
+    {
+        self.post_handler = Some(Box::new(handler));
+        self
+    }
+}
+
+/// A registered kprobe.
+struct ArmedKprobe {
+    probe: Kprobe,
+    /// The displaced instruction
+    insn: u32,
+    insn_len: usize,
+    slot: usize,
+    is_armed: AtomicBool,
+    nr_missed: AtomicU64,
+}
+
+impl ArmedKprobe {
+    fn slot_addr(&self) -> Vaddr {
+        __kprobe_xol_slots as usize + self.slot * XOL_SLOT_SIZE
+    }
+
+    fn arm(&self) {
+        let addr = self.probe.addr;
+        // SAFETY: The address is in the kernel code, which is writable. The breakpoint is
+        // written in a single store, so other CPUs see either the original instruction or
+        // the breakpoint.
+        unsafe {
+            if self.insn_len == 4 && addr % 4 == 0 {
+                core::ptr::write_volatile(addr as *mut u32, EBREAK);
+            } else {
+                core::ptr::write_volatile(addr as *mut u16, C_EBREAK);
+            }
+        }
+        flush_icache_all();
+        self.is_armed.store(true, Ordering::Relaxed);
+    }
+
+    fn disarm(&self) {
+        if !self.is_armed.swap(false, Ordering::Relaxed) {
+            return;
+        }
+        let addr = self.probe.addr;
+        // SAFETY: The address is in the kernel code, which is writable. The original
+        // instruction is restored in the same way as the breakpoint is written.
+        unsafe {
+            if self.insn_len == 4 && addr % 4 == 0 {
+                core::ptr::write_volatile(addr as *mut u32, self.insn);
+            } else {
+                core::ptr::write_volatile(addr as *mut u16, self.insn as u16);
+            }
+        }
+        flush_icache_all();
+    }
+}
+
+static KPROBES: SpinLock<Vec<Arc<ArmedKprobe>>, LocalIrqDisabled> = SpinLock::new(Vec::new());
+
+/// The kprobe being handled on the current CPU.
+enum KprobeState {
+    /// The pre-handler is running.
+    PreHandler,
+    /// The displaced instruction is being stepped.
+    Stepping {
+        kprobe: Arc<ArmedKprobe>,
+        is_irq_enabled: bool,
+    },
+    /// The post-handler is running.
+    PostHandler,
+}
+
+cpu_local! {
+    static KPROBE_STATE: RefCell<Option<KprobeState>> = RefCell::new(None);
+    /// The address of the kprobe being handled on the CPU, or zero if there is none.
+    static HANDLING_ADDR: AtomicUsize = AtomicUsize::new(0);
+}
+
+/// Registers a kprobe.
+///
+/// The probe takes effect immediately. It fails if the address is not the start of an
+/// instruction in the kernel code, if the instruction at the address cannot be executed out
+/// of line, if there is already a probe at the address, or if there are too many probes.
+pub fn register_kprobe(probe: Kprobe) -> Result<()> {
+    let addr = probe.addr;
+    let xol_slots =
+        __kprobe_xol_slots as usize..__kprobe_xol_slots as usize + NR_XOL_SLOTS * XOL_SLOT_SIZE;
+    if addr % 2 != 0
+        || !(__executable_start as usize..__etext as usize).contains(&addr)
+        || xol_slots.contains(&addr)
+    {
+        return Err(Error::InvalidArgs);
+    }
+
+    // SAFETY: The address is in the kernel code and 2-byte aligned.
+    let low = unsafe { core::ptr::read_volatile(addr as *const u16) };
+    let (insn, insn_len) = if low & 0b11 == 0b11 {
+        // SAFETY: The 32-bit instruction is in the kernel code.
+        let high = unsafe { core::ptr::read_volatile((addr + 2) as *const u16) };
+        (low as u32 | (high as u32) << 16, 4)
+    } else {
+        (low as u32, 2)
+    };
+    if !can_step_out_of_line(insn, insn_len) {
+        return Err(Error::InvalidArgs);
+    }
+
+    let mut kprobes = KPROBES.lock();
+    if kprobes.iter().any(|kprobe| kprobe.probe.addr == addr) || !is_insn_boundary(addr, &kprobes) {
+        return Err(Error::InvalidArgs);
+    }
+    let slot = (0..NR_XOL_SLOTS)
+        .find(|slot| !kprobes.iter().any(|kprobe| kprobe.slot == *slot))
+        .ok_or(Error::NotEnoughResources)?;
+
+    let kprobe = Arc::new(ArmedKprobe {
+        probe,
+        insn,
+        insn_len,
+        slot,
+        is_armed: AtomicBool::new(false),
+        nr_missed: AtomicU64::new(0),
+    });
+    // SAFETY: The slot is not used by other probes, and no CPU is stepping in the slot.
+    unsafe {
+        let slot_addr = kprobe.slot_addr();
+        if insn_len == 4 {
+            core::ptr::write_unaligned(slot_addr as *mut u32, insn);
+        } else {
+            core::ptr::write_unaligned(slot_addr as *mut u16, insn as u16);
+        }
+        core::ptr::write_unaligned((slot_addr + insn_len) as *mut u32, EBREAK);
+    }
+    kprobe.arm();
+    kprobes.push(kprobe);
+
+    Ok(())
+}
+
+/// Unregisters the kprobe at the address.
+///
+/// The function waits for the CPUs that are handling the probe, so it must not be called
+/// by the handlers.
+///
+/// Returns the number of the missed hits of the probe, or `None` if there is no probe at
+/// the address.
+pub fn unregister_kprobe(addr: Vaddr) -> Option<u64> {
+    let kprobe = {
+        let mut kprobes = KPROBES.lock();
+        let index = kprobes
+            .iter()
+            .position(|kprobe| kprobe.probe.addr == addr)?;
+        let kprobe = kprobes.remove(index);
+        kprobe.disarm();
+        kprobe
+    };
+
+    // Wait for the CPUs that are handling the probe, so that the slot can be reused.
+    for cpu in all_cpus() {
+        while HANDLING_ADDR.get_on_cpu(cpu).load(Ordering::Acquire) == addr {
+            core::hint::spin_loop();
+        }
+    }
+
+    Some(kprobe.nr_missed.load(Ordering::Relaxed))
+}
+
+/// Returns the addresses of the registered kprobes.
+pub fn kprobe_addrs() -> Vec<Vaddr> {
+    KPROBES
+        .lock()
+        .iter()
+        .map(|kprobe| kprobe.probe.addr)
+        .collect()
+}
+
+/// Handles a breakpoint exception in the kernel mode.
+///
+/// Returns whether the breakpoint is caused by the kprobes.
+pub(super) fn handle_breakpoint(f: &mut TrapFrame) -> bool {
+    let irq_guard = disable_local();
+    let mut state = KPROBE_STATE.get_with(&irq_guard).borrow_mut();
+
+    // The breakpoint after the displaced instruction is hit.
+    if let Some(KprobeState::Stepping {
+        kprobe,
+        is_irq_enabled,
+    }) = state.as_ref()
+    {
+        if f.sepc != kprobe.slot_addr() + kprobe.insn_len {
+            return false;
+        }
+        let kprobe = kprobe.clone();
+        f.sepc = kprobe.probe.addr + kprobe.insn_len;
+        if *is_irq_enabled {
+            f.sstatus |= 1 << SPIE_BIT;
+        }
+
+        *state = Some(KprobeState::PostHandler);
+        drop(state);
+        if let Some(handler) = &kprobe.probe.post_handler {
+            handler(f);
+        }
+        *KPROBE_STATE.get_with(&irq_guard).borrow_mut() = None;
+        HANDLING_ADDR
+            .get_with(&irq_guard)
+            .store(0, Ordering::Release);
+        return true;
+    }
+
+    let addr = f.sepc;
+    let kprobe = KPROBES
+        .lock()
+        .iter()
+        .find(|kprobe| kprobe.probe.addr == addr)
+        .cloned();
+    let Some(kprobe) = kprobe else {
+        // The probe may be unregistered after the breakpoint is hit. If so, the original
+        // instruction has been restored and will be executed again.
+        // SAFETY: The address has caused a breakpoint exception, so it is in the kernel code.
+        let insn = unsafe { core::ptr::read_volatile(addr as *const u16) };
+        return insn != C_EBREAK && insn != EBREAK as u16;
+    };
+
+    // The probe is hit recursively by a handler.
+    if state.is_some() {
+        kprobe.nr_missed.fetch_add(1, Ordering::Relaxed);
+        kprobe.disarm();
+        return true;
+    }
+
+    HANDLING_ADDR
+        .get_with(&irq_guard)
+        .store(addr, Ordering::Release);
+    *state = Some(KprobeState::PreHandler);
+    drop(state);
+    if let Some(handler) = &kprobe.probe.pre_handler {
+        handler(f);
+    }
+
+    // Step the displaced instruction with the local IRQs disabled, unless the pre-handler
+    // has redirected the execution.
+    if f.sepc != addr {
+        *KPROBE_STATE.get_with(&irq_guard).borrow_mut() = None;
+        HANDLING_ADDR
+            .get_with(&irq_guard)
+            .store(0, Ordering::Release);
+        return true;
+    }
+    let is_irq_enabled = f.sstatus & (1 << SPIE_BIT) != 0;
+    f.sstatus &= !(1 << SPIE_BIT);
+    f.sepc = kprobe.slot_addr();
+    *KPROBE_STATE.get_with(&irq_guard).borrow_mut() = Some(KprobeState::Stepping {
+        kprobe,
+        is_irq_enabled,
+    });
+
+    true
+}
+
+/// Returns whether the address is the start of an instruction.
+///
+/// Since the instructions are of variable lengths, they are decoded from the start of the
+/// kernel code. The breakpoints of the registered probes are decoded as the displaced
+/// instructions.
+fn is_insn_boundary(addr: Vaddr, kprobes: &[Arc<ArmedKprobe>]) -> bool {
+    let mut cursor = __executable_start as usize;
+    while cursor < addr {
+        // SAFETY: The cursor is in the kernel code and 2-byte aligned.
+        let low = unsafe { core::ptr::read_volatile(cursor as *const u16) };
+        let insn_len = if low == C_EBREAK {
+            kprobes
+                .iter()
+                .find(|kprobe| kprobe.probe.addr == cursor)
+                .map_or(2, |kprobe| kprobe.insn_len)
+        } else if low & 0b11 == 0b11 {
+            4
+        } else {
+            2
+        };
+        cursor += insn_len;
+    }
+    cursor == addr
+}
+
+/// The bit of `sstatus.SPIE`, which is restored to `sstatus.SIE` by `sret`.
+const SPIE_BIT: usize = 5;
+
+/// Returns whether the instruction can be executed at another address.
+fn can_step_out_of_line(insn: u32, insn_len: usize) -> bool {
+    if insn_len == 4 {
+        let opcode = insn & 0x7f;
+        // AUIPC, JAL, JALR, BRANCH and SYSTEM.
+        return !matches!(opcode, 0x17 | 0x6f | 0x67 | 0x63 | 0x73);
+    }
+
+    let insn = insn as u16;
+    if insn == 0 {
+        // The illegal instruction.
+        return false;
+    }
+    let quadrant = insn & 0b11;
+    let funct3 = insn >> 13;
+    match (quadrant, funct3) {
+        // C.J, C.BEQZ and C.BNEZ.
+        (0b01, 0b101 | 0b110 | 0b111) => false,
+        // C.JR, C.JALR and C.EBREAK, i.e., `rs2` is zero.
+        (0b10, 0b100) => (insn >> 2) & 0x1f != 0,
+        _ => true,
+    }
+}
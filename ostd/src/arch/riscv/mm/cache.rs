@@ -0,0 +1,134 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Cache maintenance for non-coherent DMA.
+//!
+//! Many RISC-V SoCs do not keep their DMA masters coherent with the CPU caches.
+//! The Zicbom extension provides cache-block management instructions (`cbo.clean`,
+//! `cbo.flush` and `cbo.inval`) that are used here to write back or discard the cache
+//! lines covering a DMA buffer.
+//!
+//! If the platform does not advertise Zicbom in its device tree, we fall back to a full
+//! memory fence, which is sufficient on platforms whose DMA is coherent (e.g., QEMU).
+
+use core::{
+    ops::Range,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{
+    arch::{acpi, boot::DEVICE_TREE},
+    cpu::{cpu_features, CpuFeatures},
+    mm::Vaddr,
+};
+
+/// The size of a cache block operated by the Zicbom instructions, in bytes.
+static CBOM_BLOCK_SIZE: AtomicUsize = AtomicUsize::new(DEFAULT_CBOM_BLOCK_SIZE);
+
+const DEFAULT_CBOM_BLOCK_SIZE: usize = 64;
+
+/// The kinds of cache maintenance operations.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum CacheOp {
+    /// Writes dirty cache lines back to memory, keeping them valid.
+    Clean,
+    /// Discards cache lines without writing them back.
+    Invalidate,
+    /// Writes dirty cache lines back to memory and then discards them.
+    Flush,
+}
+
+/// Detects the block size of the Zicbom extension from the device tree (or the RHCT if ACPI is
+/// used).
+pub(in crate::arch) fn init() {
+    if !cpu_features().contains(CpuFeatures::CACHE_BLOCK_MGMT) {
+        return;
+    }
+
+    let block_size = if acpi::is_enabled() {
+        acpi::rhct::cbom_block_size()
+    } else {
+        DEVICE_TREE
+            .get()
+            .unwrap()
+            .cpus()
+            .next()
+            .and_then(|cpu| cpu.property("riscv,cbom-block-size"))
+            .and_then(|prop| prop.as_usize())
+    };
+    if let Some(block_size) = block_size.filter(|size| size.is_power_of_two()) {
+        CBOM_BLOCK_SIZE.store(block_size, Ordering::Relaxed);
+    }
+
+    log::info!(
+        "Zicbom enabled, cache block size: {}",
+        CBOM_BLOCK_SIZE.load(Ordering::Relaxed)
+    );
+}
+
+/// Performs the cache maintenance operation on the virtual address range.
+///
+/// Cache blocks that are only partially covered by `range` are always flushed instead of
+/// being invalidated, so that the unrelated data sharing the blocks are not lost.
+pub(crate) fn sync_range(range: Range<Vaddr>, op: CacheOp) {
+    if range.is_empty() {
+        return;
+    }
+
+    if !cpu_features().contains(CpuFeatures::CACHE_BLOCK_MGMT) {
+        // SAFETY: A fence has no memory safety implications.
+        unsafe { core::arch::asm!("fence rw, rw", options(nostack, preserves_flags)) };
+        return;
+    }
+
+    let block_size = CBOM_BLOCK_SIZE.load(Ordering::Relaxed);
+    let start = range.start & !(block_size - 1);
+    let end = range.end.next_multiple_of(block_size);
+
+    // SAFETY: A fence has no memory safety implications.
+    unsafe { core::arch::asm!("fence rw, rw", options(nostack, preserves_flags)) };
+
+    for addr in (start..end).step_by(block_size) {
+        let is_partial = addr < range.start || addr + block_size > range.end;
+        let op = match op {
+            CacheOp::Invalidate if is_partial => CacheOp::Flush,
+            op => op,
+        };
+        // SAFETY: The address is within a mapped kernel virtual range provided by the caller.
+        // Cache-block management instructions do not change the memory contents as observed
+        // by this hart, except for `cbo.inval`, which is only applied to the blocks that are
+        // entirely owned by the DMA buffer.
+        unsafe { cbo(addr, op) };
+    }
+
+    // SAFETY: A fence has no memory safety implications.
+    unsafe { core::arch::asm!("fence rw, rw", options(nostack, preserves_flags)) };
+}
+
+/// Issues a single cache-block management instruction.
+///
+/// The instructions are encoded with `.insn` so that the assembler does not need to know
+/// about the Zicbom extension.
+///
+/// # Safety
+///
+/// The caller must ensure that `addr` is mapped and that discarding the cache block (for
+/// [`CacheOp::Invalidate`]) does not lose data still needed by the CPU.
+unsafe fn cbo(addr: Vaddr, op: CacheOp) {
+    match op {
+        CacheOp::Invalidate => core::arch::asm!(
+            ".insn i 0x0f, 2, x0, {0}, 0",
+            in(reg) addr,
+            options(nostack, preserves_flags)
+        ),
+        CacheOp::Clean => core::arch::asm!(
+            ".insn i 0x0f, 2, x0, {0}, 1",
+            in(reg) addr,
+            options(nostack, preserves_flags)
+        ),
+        CacheOp::Flush => core::arch::asm!(
+            ".insn i 0x0f, 2, x0, {0}, 2",
+            in(reg) addr,
+            options(nostack, preserves_flags)
+        ),
+    }
+}
@@ -0,0 +1,135 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The memory of the firmware, which is protected from the kernel.
+//!
+//! The M-mode firmware (e.g., OpenSBI) protects its memory with the physical memory protection
+//! (PMP), so any access to the memory traps, even a speculative one through the linear mapping.
+//! The firmware memory is found with
+//!  - the children of `/reserved-memory` in the device tree that have the `no-map` property,
+//!    which OpenSBI adds for each of its PMP regions (e.g., `mmode_resv0@80000000`); and
+//!  - the implementation ID of the SBI: the firmware that runs in the RAM is loaded below the
+//!    kernel, which is loaded at a 2 MiB offset of the memory bank as Linux does, so the memory
+//!    below the kernel is protected if none of the reserved memory covers it.
+//!
+//! The firmware memory is reserved (see [`ranges`]), is not linearly mapped, and is reported if
+//! the kernel faults when accessing it (see [`report_fault`]).
+
+use core::ops::Range;
+
+use align_ext::AlignExt;
+use spin::Once;
+
+use crate::{
+    arch::boot::DEVICE_TREE,
+    boot::memory_region::MemoryRegion,
+    mm::{kspace::LINEAR_MAPPING_VADDR_RANGE, Paddr, Vaddr, PAGE_SIZE},
+};
+
+/// The maximum number of the firmware memory ranges.
+const MAX_NR_RANGES: usize = 16;
+
+/// The SBI implementations that run in the M-mode from the RAM.
+///
+/// They are the Berkeley Boot Loader (0), OpenSBI (1) and RustSBI (4).
+const SBI_IMPL_IDS_IN_RAM: [usize; 3] = [0, 1, 4];
+
+struct FirmwareRanges {
+    ranges: [Range<Paddr>; MAX_NR_RANGES],
+    len: usize,
+}
+
+impl FirmwareRanges {
+    fn push(&mut self, range: Range<Paddr>) {
+        let range = range.start.align_down(PAGE_SIZE)..range.end.align_up(PAGE_SIZE);
+        if range.is_empty() {
+            return;
+        }
+        if self.len == MAX_NR_RANGES {
+            log::warn!("Firmware memory {:#x?} is ignored: too many ranges", range);
+            return;
+        }
+        self.ranges[self.len] = range;
+        self.len += 1;
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &Range<Paddr>> {
+        self.ranges[..self.len].iter()
+    }
+}
+
+static FIRMWARE_RANGES: Once<FirmwareRanges> = Once::new();
+
+/// Finds the firmware memory.
+///
+/// This should be called in the boot context after the device tree is parsed, and before the
+/// memory regions are parsed.
+pub(in crate::arch) fn init() {
+    let mut firmware = FirmwareRanges {
+        ranges: [const { 0..0 }; MAX_NR_RANGES],
+        len: 0,
+    };
+    let device_tree = DEVICE_TREE.get().unwrap();
+
+    let reserved_memory = device_tree.find_node("/reserved-memory");
+    for child in reserved_memory.iter().flat_map(|node| node.children()) {
+        if child.property("no-map").is_none() {
+            continue;
+        }
+        for region in child.reg().into_iter().flatten() {
+            let start = region.starting_address as usize;
+            firmware.push(start..start + region.size.unwrap_or(0));
+        }
+    }
+
+    // The memory bank of the kernel, whose memory below the kernel is not covered.
+    let kernel_start = MemoryRegion::kernel().base();
+    let is_in_ram = SBI_IMPL_IDS_IN_RAM.contains(&sbi_rt::get_sbi_impl_id());
+    let bank = device_tree
+        .find_node("/memory")
+        .into_iter()
+        .flat_map(|_| device_tree.memory().regions())
+        .map(|region| {
+            let start = region.starting_address as usize;
+            start..start + region.size.unwrap_or(0)
+        })
+        .find(|bank| bank.contains(&kernel_start))
+        .filter(|bank| {
+            is_in_ram
+                && !firmware
+                    .iter()
+                    .any(|range| range.start < kernel_start && range.end > bank.start)
+        });
+    if let Some(bank) = bank {
+        firmware.push(bank.start..kernel_start);
+    }
+
+    for range in firmware.iter() {
+        log::debug!("Firmware memory: {:#x?}", range);
+    }
+    FIRMWARE_RANGES.call_once(|| firmware);
+}
+
+/// Returns the page-aligned physical memory ranges of the firmware.
+///
+/// The ranges should be reserved and should not be linearly mapped.
+pub(crate) fn ranges() -> impl Iterator<Item = Range<Paddr>> {
+    FIRMWARE_RANGES
+        .get()
+        .into_iter()
+        .flat_map(|firmware| firmware.iter().cloned())
+}
+
+/// Reports the fault if the kernel accesses the firmware memory with the virtual address.
+pub(crate) fn report_fault(vaddr: Vaddr) {
+    if !LINEAR_MAPPING_VADDR_RANGE.contains(&vaddr) {
+        return;
+    }
+    let paddr = vaddr - LINEAR_MAPPING_VADDR_RANGE.start;
+    if let Some(range) = ranges().find(|range| range.contains(&paddr)) {
+        crate::early_println!(
+            "The kernel accessed {:#x}, which is in the firmware memory {:#x?} protected by PMP",
+            paddr,
+            range
+        );
+    }
+}
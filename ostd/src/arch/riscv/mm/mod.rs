@@ -1,9 +1,15 @@
 // SPDX-License-Identifier: MPL-2.0
 
+pub(crate) mod cache;
+pub(crate) mod firmware;
+#[cfg(ktest)]
+mod test;
+
 use alloc::fmt;
 use core::ops::Range;
 
 use crate::{
+    cpu::{cpu_features, CpuFeatures},
     mm::{
         page_prop::{CachePolicy, PageFlags, PageProperty, PrivilegedPageFlags as PrivFlags},
         page_table::PageTableEntryTrait,
@@ -84,6 +90,15 @@ pub(crate) fn tlb_flush_all_including_global() {
     riscv::asm::sfence_vma_all()
 }
 
+/// Synchronizes the instruction fetches of all harts with the modified code.
+pub(crate) fn flush_icache_all() {
+    // SAFETY: `fence.i` only synchronizes the instruction fetches of the current hart.
+    unsafe { core::arch::asm!("fence.i") };
+    // The other harts are asked to do the same by the SBI. The hart mask base of -1 means
+    // all harts.
+    let _ = sbi_rt::remote_fence_i(sbi_rt::HartMask::from_mask_base(0, usize::MAX));
+}
+
 #[derive(Clone, Copy, Pod, Default)]
 #[repr(C)]
 pub struct PageTableEntry(usize);
@@ -101,6 +116,9 @@ pub unsafe fn activate_page_table(root_paddr: Paddr, _root_pt_cache: CachePolicy
     assert!(root_paddr % PagingConsts::BASE_PAGE_SIZE == 0);
     let ppn = root_paddr >> 12;
     riscv::register::satp::set(riscv::register::satp::Mode::Sv48, 0, ppn);
+    // Writing `satp` does not flush the TLB. Since all the page tables share the ASID 0, the
+    // entries of the previous page table must be flushed.
+    riscv::asm::sfence_vma_all();
 }
 
 pub fn current_page_table_paddr() -> Paddr {
@@ -180,6 +198,12 @@ impl PageTableEntryTrait for PageTableEntry {
             | parse_flags!(prop.flags.bits(), PageFlags::R, PageTableFlags::READABLE)
             | parse_flags!(prop.flags.bits(), PageFlags::W, PageTableFlags::WRITABLE)
             | parse_flags!(prop.flags.bits(), PageFlags::X, PageTableFlags::EXECUTABLE)
+            | parse_flags!(
+                prop.flags.bits(),
+                PageFlags::ACCESSED,
+                PageTableFlags::ACCESSED
+            )
+            | parse_flags!(prop.flags.bits(), PageFlags::DIRTY, PageTableFlags::DIRTY)
             | parse_flags!(
                 prop.priv_flags.bits(),
                 PrivFlags::USER,
@@ -204,8 +228,12 @@ impl PageTableEntryTrait for PageTableEntry {
         match prop.cache {
             CachePolicy::Writeback => (),
             CachePolicy::Uncacheable => {
-                // Currently, Asterinas uses `Uncacheable` for I/O memory.
-                flags |= PageTableFlags::PBMT_IO.bits()
+                // Currently, Asterinas uses `Uncacheable` for I/O memory. Without Svpbmt, the
+                // PBMT bits are reserved, and the memory type of I/O memory is already given by
+                // the physical memory attributes.
+                if cpu_features().contains(CpuFeatures::PAGE_MEM_TYPES) {
+                    flags |= PageTableFlags::PBMT_IO.bits()
+                }
             }
             _ => panic!("unsupported cache policy"),
         }
@@ -0,0 +1,378 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Tests of the RISC-V page table entries and the page tables made of them.
+//!
+//! Sv39 and Sv48 share the format of the entries, so the page table tests also run with the
+//! paging constants of Sv39, though the kernel only activates Sv48 page tables.
+
+use alloc::vec;
+use core::{
+    mem::ManuallyDrop,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+};
+
+use super::*;
+use crate::{
+    cpu::{num_cpus, CpuId, CpuSet},
+    mm::{
+        kspace::LINEAR_MAPPING_BASE_VADDR,
+        page_size,
+        page_table::{KernelMode, PageTable, PageTableItem},
+        vm_space::VmItem,
+        FrameAllocOptions, VmIo, VmSpace,
+    },
+    prelude::*,
+    smp::inter_processor_call,
+    sync::SpinLock,
+    task::{disable_preempt, Task, TaskOptions},
+};
+
+#[derive(Clone, Debug, Default)]
+struct Sv39PagingConsts {}
+
+impl PagingConstsTrait for Sv39PagingConsts {
+    const BASE_PAGE_SIZE: usize = 4096;
+    const NR_LEVELS: PagingLevel = 3;
+    const ADDRESS_WIDTH: usize = 39;
+    const HIGHEST_TRANSLATION_LEVEL: PagingLevel = 3;
+    const PTE_SIZE: usize = core::mem::size_of::<PageTableEntry>();
+}
+
+/// A xorshift generator, which makes the fuzzing reproducible.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+fn random_prop(rng: &mut Rng) -> PageProperty {
+    let bits = rng.next();
+    PageProperty {
+        flags: PageFlags::from_bits_truncate(bits as u8),
+        cache: if bits & (1 << 8) != 0 {
+            CachePolicy::Uncacheable
+        } else {
+            CachePolicy::Writeback
+        },
+        priv_flags: PrivFlags::from_bits_truncate((bits >> 16) as u8)
+            & (PrivFlags::USER | PrivFlags::GLOBAL),
+    }
+}
+
+#[ktest]
+fn pte_bits_follow_the_spec() {
+    let paddr = 0x8020_3000;
+    let prop = PageProperty {
+        flags: PageFlags::RW | PageFlags::ACCESSED | PageFlags::DIRTY,
+        cache: CachePolicy::Writeback,
+        priv_flags: PrivFlags::USER | PrivFlags::GLOBAL,
+    };
+    let pte = PageTableEntry::new_page(paddr, 1, prop);
+    // V, R, W, U, G, A, and D.
+    assert_eq!(pte.0, (0x80203 << 10) | 0b1111_0111);
+    assert!(pte.is_last(1));
+    assert!(pte.is_last(2));
+
+    // The non-leaf entries have the V bit only.
+    let pte = PageTableEntry::new_pt(paddr);
+    assert_eq!(pte.0, (0x80203 << 10) | 0b1);
+    assert!(pte.is_present());
+    assert!(!pte.is_last(2));
+    assert_eq!(pte.paddr(), paddr);
+
+    // The PPN occupies bits 10-53, whose highest bit must be decoded.
+    let paddr = ((1 << 44) - 1) << 12;
+    let pte = PageTableEntry::new_page(paddr, 1, prop);
+    assert_eq!(pte.paddr(), paddr);
+    assert_eq!(pte.0 >> 54, 0);
+}
+
+#[ktest]
+fn pte_encoding_roundtrip() {
+    let has_pbmt = cpu_features().contains(CpuFeatures::PAGE_MEM_TYPES);
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+
+    for _ in 0..4096 {
+        let paddr = (rng.next() as usize & ((1 << 44) - 1)) << 12;
+        let level = (rng.next() % PagingConsts::NR_LEVELS as u64) as PagingLevel + 1;
+        let prop = random_prop(&mut rng);
+
+        let mut pte = PageTableEntry::new_page(paddr, level, prop);
+        assert!(pte.is_present());
+        assert_eq!(pte.paddr(), paddr);
+
+        let decoded = pte.prop();
+        assert_eq!(decoded.flags, prop.flags);
+        assert_eq!(decoded.priv_flags, prop.priv_flags);
+        let cache = if has_pbmt {
+            prop.cache
+        } else {
+            CachePolicy::Writeback
+        };
+        assert_eq!(decoded.cache, cache);
+
+        // An entry without any permission is a pointer to the next level, unless it is at the
+        // last level.
+        let is_leaf = level == 1 || prop.flags.intersects(PageFlags::RWX);
+        assert_eq!(pte.is_last(level), is_leaf, "{:?} at level {}", pte, level);
+
+        // Changing the property keeps the physical address.
+        let new_prop = random_prop(&mut rng);
+        pte.set_prop(new_prop);
+        assert_eq!(pte.paddr(), paddr);
+        assert_eq!(pte.prop().flags, new_prop.flags);
+        assert_eq!(pte.prop().priv_flags, new_prop.priv_flags);
+    }
+}
+
+/// Returns the highest level of the untracked mappings in the kernel page table, since the
+/// top two levels are reserved for the shared nodes.
+fn max_kernel_leaf_level<C: PagingConstsTrait>() -> PagingLevel {
+    C::HIGHEST_TRANSLATION_LEVEL.min(C::NR_LEVELS - 2).max(1)
+}
+
+fn protect<C: PagingConstsTrait>(
+    pt: &PageTable<KernelMode, PageTableEntry, C>,
+    range: &Range<Vaddr>,
+    mut op: impl FnMut(&mut PageProperty),
+) {
+    let mut cursor = pt.cursor_mut(range).unwrap();
+    // SAFETY: The page table is never activated.
+    while unsafe { cursor.protect_next(range.end - cursor.virt_addr(), &mut op) }.is_some() {}
+}
+
+/// Returns the lengths of the mappings and checks their physical addresses.
+#[track_caller]
+fn mapping_lens<C: PagingConstsTrait>(
+    pt: &PageTable<KernelMode, PageTableEntry, C>,
+    range: &Range<Vaddr>,
+    va_to_pa: impl Fn(Vaddr) -> Paddr,
+) -> Vec<usize> {
+    pt.cursor(range)
+        .unwrap()
+        .map(|item| match item {
+            PageTableItem::MappedUntracked { va, pa, len, .. } => {
+                assert_eq!(pa, va_to_pa(va));
+                len
+            }
+            item => panic!("Expected MappedUntracked, got {:#x?}", item),
+        })
+        .collect()
+}
+
+fn map_protect_unmap<C: PagingConstsTrait>() {
+    let pt = PageTable::<KernelMode, PageTableEntry, C>::empty();
+
+    // The range crosses a boundary of the root entries, so the nodes at all the other levels
+    // are different on its two sides. The part after the boundary is backed by a megapage if
+    // the level of megapages can be mapped.
+    let boundary = LINEAR_MAPPING_BASE_VADDR + page_size::<C>(C::NR_LEVELS);
+    let mega = page_size::<C>(2);
+    let from = boundary - PAGE_SIZE * 3..boundary + mega + PAGE_SIZE * 3;
+    let to = 0x4000_0000 - PAGE_SIZE * 3..0x4000_0000 + mega + PAGE_SIZE * 3;
+    let va_to_pa = |va: Vaddr| va - from.start + to.start;
+    let prop = PageProperty::new(PageFlags::RW, CachePolicy::Writeback);
+    // SAFETY: The page table is never activated.
+    unsafe { pt.map(&from, &to, prop).unwrap() };
+
+    for offset in (0..from.len()).step_by(PAGE_SIZE + 123) {
+        let (pa, queried_prop) = pt.query(from.start + offset).unwrap();
+        assert_eq!(pa, to.start + offset);
+        assert_eq!(queried_prop.flags, PageFlags::RW);
+    }
+    let mut lens = vec![PAGE_SIZE; 3];
+    if max_kernel_leaf_level::<C>() >= 2 {
+        lens.push(mega);
+    } else {
+        lens.extend([PAGE_SIZE; 512]);
+    }
+    lens.extend([PAGE_SIZE; 3]);
+    assert_eq!(mapping_lens(&pt, &from, va_to_pa), lens);
+
+    // Protecting a page in the megapage splits it.
+    let protected = boundary + PAGE_SIZE * 5..boundary + PAGE_SIZE * 6;
+    protect(&pt, &protected, |p| p.flags -= PageFlags::W);
+    let megapage = boundary..boundary + mega;
+    assert_eq!(mapping_lens(&pt, &megapage, va_to_pa), vec![PAGE_SIZE; 512]);
+    for va in megapage.clone().step_by(PAGE_SIZE) {
+        let (pa, queried_prop) = pt.query(va).unwrap();
+        assert_eq!(pa, va_to_pa(va));
+        if protected.contains(&va) {
+            assert_eq!(queried_prop.flags, PageFlags::R);
+        } else {
+            assert_eq!(queried_prop.flags, PageFlags::RW);
+        }
+    }
+
+    // Unmapping the pages around the boundary keeps the others.
+    let unmapped = boundary - PAGE_SIZE..boundary + PAGE_SIZE;
+    let mut cursor = pt.cursor_mut(&unmapped).unwrap();
+    for va in unmapped.clone().step_by(PAGE_SIZE) {
+        // SAFETY: The page table is never activated.
+        let item = unsafe { cursor.take_next(unmapped.end - cursor.virt_addr()) };
+        let PageTableItem::MappedUntracked { va: item_va, .. } = item else {
+            panic!("Expected MappedUntracked, got {:#x?}", item);
+        };
+        assert_eq!(item_va, va);
+    }
+    drop(cursor);
+    assert!(pt.query(unmapped.start).is_none());
+    assert!(pt.query(unmapped.end - 1).is_none());
+    assert_eq!(
+        pt.query(unmapped.start - 1).unwrap().0,
+        va_to_pa(unmapped.start - 1)
+    );
+    assert_eq!(pt.query(unmapped.end).unwrap().0, va_to_pa(unmapped.end));
+
+    // Since untracked mappings cannot be dropped, we just leak it here.
+    let _ = ManuallyDrop::new(pt);
+}
+
+#[ktest]
+fn map_protect_unmap_sv39() {
+    map_protect_unmap::<Sv39PagingConsts>();
+}
+
+#[ktest]
+fn map_protect_unmap_sv48() {
+    map_protect_unmap::<PagingConsts>();
+}
+
+/// Creates a `VmSpace` that maps a frame containing the value at the virtual address.
+fn new_vm_space_with_value(va: Vaddr, value: u64) -> Arc<VmSpace> {
+    let frame = FrameAllocOptions::new().alloc_frame().unwrap();
+    frame.write_val(0, &value).unwrap();
+
+    let vm_space = Arc::new(VmSpace::new());
+    let prop = PageProperty::new(PageFlags::RW, CachePolicy::Writeback);
+    vm_space
+        .cursor_mut(&(va..va + PAGE_SIZE))
+        .unwrap()
+        .map(frame.into(), prop);
+    vm_space
+}
+
+#[ktest]
+fn switch_address_spaces() {
+    // All the address spaces share the ASID 0, so no stale translation should be used after
+    // switching between them.
+    let va = PAGE_SIZE * 16;
+    let vm_spaces = [
+        new_vm_space_with_value(va, 0),
+        new_vm_space_with_value(va, 1),
+    ];
+
+    let _preempt_guard = disable_preempt();
+    for i in [0, 1, 0, 1, 1, 0] {
+        vm_spaces[i].activate();
+        let value: u64 = vm_spaces[i].reader(va, 8).unwrap().read_val().unwrap();
+        assert_eq!(value, i as u64);
+    }
+}
+
+/// The address space and the address that [`remote_read`] reads through.
+static REMOTE_READ_TARGET: SpinLock<Option<(Arc<VmSpace>, Vaddr)>> = SpinLock::new(None);
+/// The value that [`remote_read`] has read, or [`NOT_READ`].
+static REMOTE_READ_VALUE: AtomicU64 = AtomicU64::new(NOT_READ);
+const NOT_READ: u64 = u64::MAX;
+
+/// Reads through the mapping on the CPU that handles the inter-processor call.
+///
+/// The address space is activated on that CPU, so the CPU is a target of the TLB shootdowns.
+fn remote_read() {
+    let (vm_space, va) = REMOTE_READ_TARGET.lock().clone().unwrap();
+    vm_space.activate();
+    let value = vm_space.reader(va, 8).unwrap().read_val().unwrap();
+    REMOTE_READ_VALUE.store(value, Ordering::Release);
+}
+
+/// Reads through the mapping on the remote CPU and waits for the result.
+fn read_on(cpu: CpuId, vm_space: &Arc<VmSpace>, va: Vaddr) -> u64 {
+    *REMOTE_READ_TARGET.lock() = Some((vm_space.clone(), va));
+    REMOTE_READ_VALUE.store(NOT_READ, Ordering::Relaxed);
+    inter_processor_call(&CpuSet::from(cpu), remote_read);
+    loop {
+        let value = REMOTE_READ_VALUE.load(Ordering::Acquire);
+        if value != NOT_READ {
+            return value;
+        }
+        core::hint::spin_loop();
+    }
+}
+
+#[ktest]
+fn concurrent_map_unmap_with_shootdowns() {
+    const NR_TASKS: usize = 4;
+    const NR_ROUNDS: usize = 64;
+
+    let vm_space = Arc::new(VmSpace::new());
+    let nr_done = Arc::new(AtomicUsize::new(0));
+
+    for i in 0..NR_TASKS {
+        let vm_space = vm_space.clone();
+        let nr_done = nr_done.clone();
+        TaskOptions::new(move || {
+            // The unmapping flushes the TLBs of all the CPUs that activate the space.
+            vm_space.activate();
+
+            let range = PAGE_SIZE * 512 * (i + 1)..PAGE_SIZE * (512 * (i + 1) + 4);
+            let prop = PageProperty::new(PageFlags::RW, CachePolicy::Writeback);
+            for _ in 0..NR_ROUNDS {
+                let mut cursor = vm_space.cursor_mut(&range).unwrap();
+                for _ in range.clone().step_by(PAGE_SIZE) {
+                    let frame = FrameAllocOptions::new().alloc_frame().unwrap();
+                    cursor.map(frame.into(), prop);
+                }
+                drop(cursor);
+
+                let mut cursor = vm_space.cursor_mut(&range).unwrap();
+                cursor.unmap(range.len());
+                cursor.flusher().sync_tlb_flush();
+                drop(cursor);
+
+                let mut cursor = vm_space.cursor(&range).unwrap();
+                assert!(matches!(cursor.query(), Ok(VmItem::NotMapped { .. })));
+                Task::yield_now();
+            }
+
+            nr_done.fetch_add(1, Ordering::Release);
+        })
+        .data(())
+        .spawn()
+        .unwrap();
+    }
+
+    // The tasks above all run on the BSP, so another CPU reads through a mapping that is
+    // remapped to different frames in the meantime. A stale TLB entry on that CPU would make
+    // it read the contents of the previous frame.
+    if num_cpus() > 1 {
+        let remote_cpu = CpuId::try_from(1).unwrap();
+        let va = PAGE_SIZE * 512 * (NR_TASKS + 1);
+        let prop = PageProperty::new(PageFlags::RW, CachePolicy::Writeback);
+        for round in 0..NR_ROUNDS as u64 {
+            let frame = FrameAllocOptions::new().alloc_frame().unwrap();
+            frame.write_val(0, &round).unwrap();
+
+            let mut cursor = vm_space.cursor_mut(&(va..va + PAGE_SIZE)).unwrap();
+            cursor.map(frame.into(), prop);
+            cursor.flusher().sync_tlb_flush();
+            drop(cursor);
+
+            assert_eq!(read_on(remote_cpu, &vm_space, va), round);
+            Task::yield_now();
+        }
+
+        let mut cursor = vm_space.cursor_mut(&(va..va + PAGE_SIZE)).unwrap();
+        cursor.unmap(PAGE_SIZE);
+        cursor.flusher().sync_tlb_flush();
+    }
+
+    while nr_done.load(Ordering::Acquire) < NR_TASKS {
+        Task::yield_now();
+    }
+}
@@ -2,18 +2,30 @@
 
 //! Platform-specific code for the RISC-V platform.
 
+pub(crate) mod acpi;
+mod allocator;
 pub mod boot;
 pub(crate) mod cpu;
+pub mod crypto;
 pub mod device;
+pub mod hart;
 pub mod iommu;
 pub(crate) mod irq;
+pub mod isa;
+pub mod kexec;
+pub mod kprobe;
 pub(crate) mod mm;
+pub(crate) mod module;
 pub(crate) mod pci;
+pub mod pmu;
+pub(crate) mod power;
 pub mod qemu;
+pub mod ramoops;
 pub mod serial;
 pub mod task;
 pub mod timer;
 pub mod trap;
+pub mod virt;
 
 use core::sync::atomic::Ordering;
 
@@ -43,11 +55,29 @@ pub(crate) unsafe fn late_init_on_bsp() {
         trap::init(true);
     }
     irq::init();
+    isa::init();
+    mm::cache::init();
+    virt::init();
+    cpu::context::init_compat_mode();
+    kexec::init();
+    ramoops::init();
+    pmu::init();
+
+    let io_mem_builder = allocator::construct_io_mem_allocator_builder();
 
     // SAFETY: we're on the BSP and we're ready to boot all APs.
     unsafe { crate::boot::smp::boot_all_aps() };
 
     timer::init();
+
+    // SAFETY: All the system device memory I/Os have been removed from the builder.
+    unsafe {
+        crate::io::init(io_mem_builder);
+    }
+
+    irq::plic::init();
+    qemu::init();
+
     let _ = pci::init();
 }
 
@@ -73,11 +103,18 @@ pub fn read_random() -> Option<u64> {
 }
 
 pub(crate) unsafe fn init_on_ap() {
-    unimplemented!()
+    irq::init_on_ap();
+    timer::sync::sync_on_ap(crate::cpu::current_cpu_racy().as_usize() as u32);
 }
 
 pub(crate) fn interrupts_ack(irq_number: usize) {
-    unimplemented!()
+    irq::complete(irq_number);
+}
+
+/// Restarts the system.
+pub fn reboot() -> ! {
+    sbi_rt::system_reset(sbi_rt::ColdReboot, sbi_rt::NoReason);
+    unreachable!("the system does not restart");
 }
 
 /// Return the frequency of TSC. The unit is Hz.
@@ -86,8 +123,17 @@ pub fn tsc_freq() -> u64 {
 }
 
 /// Reads the current value of the processor’s time-stamp counter (TSC).
+///
+/// The value is the `time` CSR, which is compensated if the harts are not synchronized.
 pub fn read_tsc() -> u64 {
-    riscv::register::time::read64()
+    timer::sync::read_time()
+}
+
+/// Reads the clock of the scheduler, in the units of the TSC.
+///
+/// Unlike [`read_tsc`], the clock is monotonic even if the caller migrates across the harts.
+pub fn sched_clock() -> u64 {
+    timer::sync::sched_clock()
 }
 
 pub(crate) fn enable_cpu_features() {
@@ -0,0 +1,354 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The relocations of the kernel extensions on RISC-V.
+//!
+//! The relocations are applied as the RISC-V ELF psABI specifies. The linker
+//! relaxation is not supported, so the extensions should be built without it
+//! (e.g., with `-C target-feature=-relax`); the `R_RISCV_RELAX` and the
+//! `R_RISCV_ALIGN` relocations are ignored since the unrelaxed code is already
+//! correct and aligned.
+
+use alloc::collections::BTreeMap;
+
+use crate::{
+    mm::Vaddr,
+    module::{ModuleError, Relocation},
+};
+
+/// The machine of the ELF objects, which is `EM_RISCV`.
+pub(crate) const ELF_MACHINE: u16 = 243;
+
+const R_RISCV_NONE: u32 = 0;
+const R_RISCV_32: u32 = 1;
+const R_RISCV_64: u32 = 2;
+const R_RISCV_BRANCH: u32 = 16;
+const R_RISCV_JAL: u32 = 17;
+const R_RISCV_CALL: u32 = 18;
+const R_RISCV_CALL_PLT: u32 = 19;
+const R_RISCV_GOT_HI20: u32 = 20;
+const R_RISCV_PCREL_HI20: u32 = 23;
+const R_RISCV_PCREL_LO12_I: u32 = 24;
+const R_RISCV_PCREL_LO12_S: u32 = 25;
+const R_RISCV_HI20: u32 = 26;
+const R_RISCV_LO12_I: u32 = 27;
+const R_RISCV_LO12_S: u32 = 28;
+const R_RISCV_ADD8: u32 = 33;
+const R_RISCV_ADD16: u32 = 34;
+const R_RISCV_ADD32: u32 = 35;
+const R_RISCV_ADD64: u32 = 36;
+const R_RISCV_SUB8: u32 = 37;
+const R_RISCV_SUB16: u32 = 38;
+const R_RISCV_SUB32: u32 = 39;
+const R_RISCV_SUB64: u32 = 40;
+const R_RISCV_ALIGN: u32 = 43;
+const R_RISCV_RVC_BRANCH: u32 = 44;
+const R_RISCV_RVC_JUMP: u32 = 45;
+const R_RISCV_RELAX: u32 = 51;
+const R_RISCV_SUB6: u32 = 52;
+const R_RISCV_SET6: u32 = 53;
+const R_RISCV_SET8: u32 = 54;
+const R_RISCV_SET16: u32 = 55;
+const R_RISCV_SET32: u32 = 56;
+const R_RISCV_32_PCREL: u32 = 57;
+
+/// Returns whether the relocation refers to the GOT entry of its symbol.
+pub(crate) fn needs_got_entry(typ: u32) -> bool {
+    typ == R_RISCV_GOT_HI20
+}
+
+/// Applies the relocations of a section.
+///
+/// # Safety
+///
+/// The places of the relocations must be valid for writes before the ends
+/// of their sections.
+pub(crate) unsafe fn apply_relocations(relocs: &[Relocation]) -> Result<(), ModuleError> {
+    // The `%pcrel_lo` relocations refer to the `auipc` instructions, whose
+    // offsets are found with the `%pcrel_hi` relocations.
+    let mut hi20_offsets = BTreeMap::new();
+    for reloc in relocs {
+        match reloc.typ {
+            R_RISCV_PCREL_HI20 => {
+                hi20_offsets.insert(reloc.place, reloc.pcrel());
+            }
+            R_RISCV_GOT_HI20 => {
+                let got_entry = reloc.got_entry.unwrap() as i64;
+                hi20_offsets.insert(reloc.place, got_entry.wrapping_sub(reloc.place as i64));
+            }
+            _ => {}
+        }
+    }
+
+    for reloc in relocs {
+        // SAFETY: The caller guarantees that the place is valid.
+        unsafe { apply(reloc, &hi20_offsets)? };
+    }
+    Ok(())
+}
+
+/// Applies a relocation.
+///
+/// # Safety
+///
+/// The place must be valid for writes before the end of its section.
+unsafe fn apply(
+    reloc: &Relocation,
+    hi20_offsets: &BTreeMap<Vaddr, i64>,
+) -> Result<(), ModuleError> {
+    let typ = reloc.typ;
+    let value = reloc.value() as i64;
+    let pcrel = reloc.pcrel();
+    let overflow = || ModuleError::RelocationOverflow(typ);
+
+    let width = match typ {
+        R_RISCV_NONE | R_RISCV_ALIGN | R_RISCV_RELAX => 0,
+        R_RISCV_ADD8 | R_RISCV_SUB8 | R_RISCV_SUB6 | R_RISCV_SET6 | R_RISCV_SET8 => 1,
+        R_RISCV_ADD16 | R_RISCV_SUB16 | R_RISCV_SET16 | R_RISCV_RVC_BRANCH | R_RISCV_RVC_JUMP => 2,
+        R_RISCV_CALL | R_RISCV_CALL_PLT | R_RISCV_ADD64 | R_RISCV_SUB64 | R_RISCV_64 => 8,
+        _ => 4,
+    };
+    if reloc
+        .place
+        .checked_add(width)
+        .is_none_or(|end| end > reloc.section_end)
+    {
+        return Err(ModuleError::InvalidImage("relocation out of the section"));
+    }
+    let ptr = reloc.place as *mut u8;
+
+    // SAFETY: The place is valid for writes of `width` bytes, as checked
+    // above and guaranteed by the caller. The code may be misaligned due to
+    // the compressed instructions.
+    unsafe {
+        match typ {
+            R_RISCV_NONE | R_RISCV_ALIGN | R_RISCV_RELAX => {}
+            R_RISCV_32 => {
+                if i32::try_from(value).is_err() && u32::try_from(value).is_err() {
+                    return Err(overflow());
+                }
+                write::<u32>(ptr, value as u32);
+            }
+            R_RISCV_64 => write::<u64>(ptr, value as u64),
+            R_RISCV_32_PCREL => {
+                let pcrel = i32::try_from(pcrel).map_err(|_| overflow())?;
+                write::<u32>(ptr, pcrel as u32);
+            }
+            R_RISCV_BRANCH => {
+                check_offset(pcrel, 13).ok_or_else(overflow)?;
+                update::<u32>(ptr, |insn| (insn & 0x01ff_f07f) | b_imm(pcrel));
+            }
+            R_RISCV_JAL => {
+                check_offset(pcrel, 21).ok_or_else(overflow)?;
+                update::<u32>(ptr, |insn| (insn & 0x0000_0fff) | j_imm(pcrel));
+            }
+            R_RISCV_CALL | R_RISCV_CALL_PLT => {
+                check_hi20(pcrel).ok_or_else(overflow)?;
+                update::<u32>(ptr, |insn| (insn & 0x0000_0fff) | u_imm(pcrel));
+                update::<u32>(ptr.add(4), |insn| (insn & 0x000f_ffff) | i_imm(pcrel));
+            }
+            R_RISCV_PCREL_HI20 | R_RISCV_GOT_HI20 => {
+                let offset = hi20_offsets[&reloc.place];
+                check_hi20(offset).ok_or_else(overflow)?;
+                update::<u32>(ptr, |insn| (insn & 0x0000_0fff) | u_imm(offset));
+            }
+            R_RISCV_PCREL_LO12_I | R_RISCV_PCREL_LO12_S => {
+                // The symbol is the label of the `auipc` instruction.
+                let offset = *hi20_offsets
+                    .get(&(reloc.sym_value as Vaddr))
+                    .ok_or(ModuleError::InvalidImage("%pcrel_lo without %pcrel_hi"))?;
+                if typ == R_RISCV_PCREL_LO12_I {
+                    update::<u32>(ptr, |insn| (insn & 0x000f_ffff) | i_imm(offset));
+                } else {
+                    update::<u32>(ptr, |insn| (insn & 0x01ff_f07f) | s_imm(offset));
+                }
+            }
+            R_RISCV_HI20 => {
+                check_hi20(value).ok_or_else(overflow)?;
+                update::<u32>(ptr, |insn| (insn & 0x0000_0fff) | u_imm(value));
+            }
+            R_RISCV_LO12_I => update::<u32>(ptr, |insn| (insn & 0x000f_ffff) | i_imm(value)),
+            R_RISCV_LO12_S => update::<u32>(ptr, |insn| (insn & 0x01ff_f07f) | s_imm(value)),
+            R_RISCV_RVC_BRANCH => {
+                check_offset(pcrel, 9).ok_or_else(overflow)?;
+                update::<u16>(ptr, |insn| (insn & 0xe383) | cb_imm(pcrel));
+            }
+            R_RISCV_RVC_JUMP => {
+                check_offset(pcrel, 12).ok_or_else(overflow)?;
+                update::<u16>(ptr, |insn| (insn & 0xe003) | cj_imm(pcrel));
+            }
+            R_RISCV_ADD8 => update::<u8>(ptr, |old| old.wrapping_add(value as u8)),
+            R_RISCV_ADD16 => update::<u16>(ptr, |old| old.wrapping_add(value as u16)),
+            R_RISCV_ADD32 => update::<u32>(ptr, |old| old.wrapping_add(value as u32)),
+            R_RISCV_ADD64 => update::<u64>(ptr, |old| old.wrapping_add(value as u64)),
+            R_RISCV_SUB6 => update::<u8>(ptr, |old| {
+                (old & 0xc0) | (old.wrapping_sub(value as u8) & 0x3f)
+            }),
+            R_RISCV_SUB8 => update::<u8>(ptr, |old| old.wrapping_sub(value as u8)),
+            R_RISCV_SUB16 => update::<u16>(ptr, |old| old.wrapping_sub(value as u16)),
+            R_RISCV_SUB32 => update::<u32>(ptr, |old| old.wrapping_sub(value as u32)),
+            R_RISCV_SUB64 => update::<u64>(ptr, |old| old.wrapping_sub(value as u64)),
+            R_RISCV_SET6 => update::<u8>(ptr, |old| (old & 0xc0) | (value as u8 & 0x3f)),
+            R_RISCV_SET8 => write::<u8>(ptr, value as u8),
+            R_RISCV_SET16 => write::<u16>(ptr, value as u16),
+            R_RISCV_SET32 => write::<u32>(ptr, value as u32),
+            _ => return Err(ModuleError::UnsupportedRelocation(typ)),
+        }
+    }
+    Ok(())
+}
+
+/// Reads and writes a possibly misaligned value.
+///
+/// # Safety
+///
+/// The pointer must be valid for reads and writes.
+unsafe fn update<T: Copy>(ptr: *mut u8, f: impl FnOnce(T) -> T) {
+    let ptr = ptr.cast::<T>();
+    // SAFETY: The caller guarantees that the pointer is valid.
+    unsafe { ptr.write_unaligned(f(ptr.read_unaligned())) };
+}
+
+/// Writes a possibly misaligned value.
+///
+/// # Safety
+///
+/// The pointer must be valid for writes.
+unsafe fn write<T: Copy>(ptr: *mut u8, value: T) {
+    // SAFETY: The caller guarantees that the pointer is valid.
+    unsafe { ptr.cast::<T>().write_unaligned(value) };
+}
+
+/// Checks that the offset is even and fits in a signed immediate of the bits.
+fn check_offset(offset: i64, bits: u32) -> Option<()> {
+    let limit = 1i64 << (bits - 1);
+    (offset % 2 == 0 && (-limit..limit).contains(&offset)).then_some(())
+}
+
+/// Checks that the value can be split into a `%hi` part and a `%lo` part.
+fn check_hi20(value: i64) -> Option<()> {
+    i32::try_from(value.wrapping_add(0x800)).ok().map(|_| ())
+}
+
+/// Encodes the `%hi` part of the value as the immediate of a U-type instruction.
+fn u_imm(value: i64) -> u32 {
+    ((value.wrapping_add(0x800) as u32) >> 12) << 12
+}
+
+/// Encodes the `%lo` part of the value as the immediate of an I-type instruction.
+fn i_imm(value: i64) -> u32 {
+    (value as u32 & 0xfff) << 20
+}
+
+/// Encodes the `%lo` part of the value as the immediate of an S-type instruction.
+fn s_imm(value: i64) -> u32 {
+    let value = value as u32;
+    ((value & 0xfe0) << 20) | ((value & 0x1f) << 7)
+}
+
+/// Encodes the offset as the immediate of a B-type instruction.
+fn b_imm(offset: i64) -> u32 {
+    let offset = offset as u32;
+    ((offset & 0x1000) << 19)
+        | ((offset & 0x7e0) << 20)
+        | ((offset & 0x1e) << 7)
+        | ((offset & 0x800) >> 4)
+}
+
+/// Encodes the offset as the immediate of a J-type instruction.
+fn j_imm(offset: i64) -> u32 {
+    let offset = offset as u32;
+    ((offset & 0x10_0000) << 11)
+        | ((offset & 0x7fe) << 20)
+        | ((offset & 0x800) << 9)
+        | (offset & 0xf_f000)
+}
+
+/// Encodes the offset as the immediate of a `c.beqz` or a `c.bnez` instruction.
+fn cb_imm(offset: i64) -> u16 {
+    let offset = offset as u16;
+    ((offset & 0x100) << 4)
+        | ((offset & 0x18) << 7)
+        | ((offset & 0xc0) >> 1)
+        | ((offset & 0x6) << 2)
+        | ((offset & 0x20) >> 3)
+}
+
+/// Encodes the offset as the immediate of a `c.j` instruction.
+fn cj_imm(offset: i64) -> u16 {
+    let offset = offset as u16;
+    ((offset & 0x800) << 1)
+        | ((offset & 0x10) << 7)
+        | ((offset & 0x300) << 1)
+        | ((offset & 0x400) >> 2)
+        | ((offset & 0x40) << 1)
+        | ((offset & 0x80) >> 1)
+        | ((offset & 0xe) << 2)
+        | ((offset & 0x20) >> 3)
+}
+
+#[cfg(ktest)]
+mod test {
+    use alloc::vec;
+
+    use super::*;
+    use crate::prelude::ktest;
+
+    /// Applies a relocation to the code at the symbol with the offset.
+    fn relocate(code: &mut [u32], typ: u32, offset: i64) -> Result<(), ModuleError> {
+        let place = code.as_mut_ptr() as Vaddr;
+        let reloc = Relocation {
+            typ,
+            place,
+            section_end: place + code.len() * 4,
+            sym_value: (place as i64 + offset) as u64,
+            addend: 0,
+            got_entry: None,
+        };
+        // SAFETY: The code is valid for writes.
+        unsafe { apply_relocations(&[reloc]) }
+    }
+
+    #[ktest]
+    fn relocate_jumps() {
+        // jal ra, 0 => jal ra, 16
+        let mut code = vec![0x0000_00ef];
+        relocate(&mut code, R_RISCV_JAL, 16).unwrap();
+        assert_eq!(code[0], 0x0100_00ef);
+
+        // beq a0, a1, 0 => beq a0, a1, 8
+        let mut code = vec![0x00b5_0063];
+        relocate(&mut code, R_RISCV_BRANCH, 8).unwrap();
+        assert_eq!(code[0], 0x00b5_0463);
+
+        // auipc ra, 0; jalr ra, 0(ra) => auipc ra, 0x12346; jalr ra, -4(ra)
+        let mut code = vec![0x0000_0097, 0x0000_80e7];
+        relocate(&mut code, R_RISCV_CALL, 0x1234_5ffc).unwrap();
+        assert_eq!(code, [0x1234_6097, 0xffc0_80e7]);
+
+        // c.j 0 => c.j 2
+        let mut code = vec![0x0000_a001];
+        relocate(&mut code, R_RISCV_RVC_JUMP, 2).unwrap();
+        assert_eq!(code[0], 0x0000_a009);
+    }
+
+    #[ktest]
+    fn relocate_out_of_range() {
+        let mut code = vec![0x0000_00ef];
+        assert_eq!(
+            relocate(&mut code, R_RISCV_JAL, 1 << 20),
+            Err(ModuleError::RelocationOverflow(R_RISCV_JAL))
+        );
+        let mut code = vec![0x0000_0097, 0x0000_80e7];
+        assert_eq!(
+            relocate(&mut code, R_RISCV_CALL, 1 << 31),
+            Err(ModuleError::RelocationOverflow(R_RISCV_CALL))
+        );
+        // The place is too close to the end of the section.
+        let mut code = vec![0x0000_0097];
+        assert!(relocate(&mut code, R_RISCV_CALL, 0).is_err());
+        assert_eq!(
+            relocate(&mut code, 0xff, 0),
+            Err(ModuleError::UnsupportedRelocation(0xff))
+        );
+    }
+}
@@ -2,32 +2,83 @@
 
 //! PCI bus access
 
+use core::ops::{Range, RangeInclusive};
+
 use spin::Once;
 
-use super::boot::DEVICE_TREE;
+use super::{acpi, boot::DEVICE_TREE};
 use crate::{bus::pci::PciDeviceLocation, io::IoMem, mm::VmIoOnce, prelude::*, Error};
 
-static PCI_BASE_ADDR: Once<IoMem> = Once::new();
+/// The memory-mapped configuration space of the PCI buses.
+struct ConfigSpace {
+    io_mem: IoMem,
+    /// The buses in the region, the first of which is at the start of the region.
+    buses: RangeInclusive<u8>,
+    /// Whether the region is of the ECAM of PCIe, or of the CAM of the legacy PCI.
+    is_ecam: bool,
+}
+
+static CONFIG_SPACE: Once<ConfigSpace> = Once::new();
 
 pub(crate) fn write32(location: &PciDeviceLocation, offset: u32, value: u32) -> Result<()> {
-    PCI_BASE_ADDR.get().ok_or(Error::IoError)?.write_once(
-        (encode_as_address_offset(location) | (offset & 0xfc)) as usize,
-        &value,
-    )
+    let config_space = CONFIG_SPACE.get().ok_or(Error::IoError)?;
+    let Some(offset) = config_space.offset_of(location, offset) else {
+        // There are no devices on the bus.
+        return Ok(());
+    };
+    config_space.io_mem.write_once(offset, &value)
 }
 
 pub(crate) fn read32(location: &PciDeviceLocation, offset: u32) -> Result<u32> {
-    PCI_BASE_ADDR
-        .get()
-        .ok_or(Error::IoError)?
-        .read_once((encode_as_address_offset(location) | (offset & 0xfc)) as usize)
+    let config_space = CONFIG_SPACE.get().ok_or(Error::IoError)?;
+    let Some(offset) = config_space.offset_of(location, offset) else {
+        // Like the reads of the absent devices, the reads on the absent buses return all ones.
+        return Ok(u32::MAX);
+    };
+    config_space.io_mem.read_once(offset)
 }
 
 pub(crate) fn has_pci_bus() -> bool {
-    PCI_BASE_ADDR.is_completed()
+    CONFIG_SPACE.is_completed()
 }
 
+/// Finds the configuration space in the device tree (or the MCFG if ACPI is used).
 pub(crate) fn init() -> Result<()> {
+    let (region, buses, is_ecam) = if acpi::is_enabled() {
+        find_in_mcfg()?
+    } else {
+        find_in_device_tree()?
+    };
+
+    // The buses out of the region are ignored.
+    let bus_shift = if is_ecam {
+        ECAM_BUS_SHIFT
+    } else {
+        CAM_BUS_SHIFT
+    };
+    let nr_buses = region.len() >> bus_shift;
+    if nr_buses == 0 || buses.is_empty() {
+        warn!("The PCI configuration space {:#x?} has no buses", region);
+        return Err(Error::IoError);
+    }
+    let last_bus = (*buses.start() as usize + nr_buses - 1).min(*buses.end() as usize);
+    let buses = *buses.start()..=last_bus as u8;
+
+    let io_mem = IoMem::acquire(region)?;
+    CONFIG_SPACE.call_once(|| ConfigSpace {
+        io_mem,
+        buses,
+        is_ecam,
+    });
+
+    Ok(())
+}
+
+const ECAM_BUS_SHIFT: u32 = 20;
+const CAM_BUS_SHIFT: u32 = 16;
+
+/// Returns the region, the buses and whether the region is of the ECAM.
+fn find_in_device_tree() -> Result<(Range<usize>, RangeInclusive<u8>, bool)> {
     let pci = DEVICE_TREE
         .get()
         .unwrap()
@@ -36,7 +87,7 @@ pub(crate) fn init() -> Result<()> {
 
     let mut reg = pci.reg().ok_or(Error::IoError)?;
 
-    let Ok(region) = reg.next() else {
+    let Some(region) = reg.next() else {
         warn!("PCI node should have exactly one `reg` property, but found zero `reg`s");
         return Err(Error::IoError);
     };
@@ -48,20 +99,54 @@ pub(crate) fn init() -> Result<()> {
         return Err(Error::IoError);
     }
 
-    PCI_BASE_ADDR.call_once(|| {
-        IoMem::acquire(
-            (region.starting_address as usize)
-                ..(region.starting_address as usize + region.size.unwrap()),
-        )
-        .unwrap()
-    });
+    let is_ecam = pci
+        .compatible()
+        .is_some_and(|compatible| compatible.all().any(|name| name == "pci-host-ecam-generic"));
+    let buses = pci
+        .property("bus-range")
+        .and_then(|prop| {
+            let start = u32::from_be_bytes(prop.value.get(0..4)?.try_into().unwrap());
+            let end = u32::from_be_bytes(prop.value.get(4..8)?.try_into().unwrap());
+            Some(start.min(u8::MAX as u32) as u8..=end.min(u8::MAX as u32) as u8)
+        })
+        .unwrap_or(0..=u8::MAX);
 
-    Ok(())
+    let start = region.starting_address as usize;
+    let size = region.size.ok_or(Error::IoError)?;
+    Ok((start..start + size, buses, is_ecam))
 }
 
-/// Encodes the bus, device, and function into an address offset in the PCI MMIO region.
-fn encode_as_address_offset(location: &PciDeviceLocation) -> u32 {
-    ((location.bus as u32) << 16)
-        | ((location.device as u32) << 11)
-        | ((location.function as u32) << 8)
+/// Returns the same as [`find_in_device_tree`], but for the segment group zero in the MCFG.
+fn find_in_mcfg() -> Result<(Range<usize>, RangeInclusive<u8>, bool)> {
+    let ecam = acpi::mcfg::ecam_regions()
+        .find(|ecam| ecam.segment == 0 && !ecam.buses.is_empty())
+        .ok_or(Error::IoError)?;
+
+    let start = ecam.base + ((*ecam.buses.start() as usize) << ECAM_BUS_SHIFT);
+    let end = ecam.base + ((*ecam.buses.end() as usize + 1) << ECAM_BUS_SHIFT);
+    Ok((start..end, ecam.buses, true))
+}
+
+impl ConfigSpace {
+    /// Returns the offset of the register of the device in the region, if its bus is in the
+    /// region.
+    fn offset_of(&self, location: &PciDeviceLocation, offset: u32) -> Option<usize> {
+        if !self.buses.contains(&location.bus) {
+            return None;
+        }
+        let bus = (location.bus - self.buses.start()) as usize;
+
+        // The configuration space of each function is 4 KiB in ECAM, and is 256 bytes in CAM.
+        let (bus_shift, offset_mask) = if self.is_ecam {
+            (ECAM_BUS_SHIFT, 0xffc)
+        } else {
+            (CAM_BUS_SHIFT, 0xfc)
+        };
+        Some(
+            (bus << bus_shift)
+                | ((location.device as usize) << (bus_shift - 5))
+                | ((location.function as usize) << (bus_shift - 8))
+                | (offset & offset_mask) as usize,
+        )
+    }
 }
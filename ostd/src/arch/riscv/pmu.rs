@@ -0,0 +1,275 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The performance monitoring unit (PMU), which is managed by the SBI PMU extension.
+//!
+//! The hardware performance counters of RISC-V can only be configured in the machine mode, so
+//! a counter is allocated, started and stopped by the SBI firmware (see [`PmuCounter`]). Once
+//! started, the counter is read directly from its CSR (e.g., `cycle` or `hpmcounter3`), which
+//! is much cheaper than calling the firmware. The counters of the firmware events (e.g., the
+//! traps handled by the firmware) have no CSRs, so they are read by calling the firmware.
+//!
+//! The counters are local to each CPU. A counter must be read and stopped on the CPU that
+//! started it.
+//!
+//! Reference: <https://github.com/riscv-non-isa/riscv-sbi-doc/blob/master/src/ext-pmu.adoc>
+
+use core::{
+    arch::asm,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{
+    cpu::{cpu_features, CpuFeatures, CpuId, PinCurrentCpu},
+    prelude::*,
+    Error,
+};
+
+/// The ID of the SBI PMU extension, i.e., "PMU" in ASCII.
+const EID_PMU: usize = 0x504D55;
+const FID_NUM_COUNTERS: usize = 0;
+const FID_COUNTER_GET_INFO: usize = 1;
+const FID_COUNTER_CONFIG_MATCHING: usize = 2;
+const FID_COUNTER_START: usize = 3;
+const FID_COUNTER_STOP: usize = 4;
+const FID_COUNTER_FW_READ: usize = 5;
+
+const CFG_FLAG_CLEAR_VALUE: usize = 1 << 1;
+const CFG_FLAG_SET_UINH: usize = 1 << 5;
+const CFG_FLAG_SET_SINH: usize = 1 << 6;
+const CFG_FLAG_SET_MINH: usize = 1 << 7;
+
+const START_FLAG_SET_INIT_VALUE: usize = 1 << 0;
+const STOP_FLAG_RESET: usize = 1 << 0;
+
+/// The base of the CSRs of the counters, i.e., the CSR number of `cycle`.
+const CSR_CYCLE: usize = 0xC00;
+
+/// The number of the counters, or zero if the SBI PMU extension is not available.
+static NR_COUNTERS: AtomicUsize = AtomicUsize::new(0);
+
+pub(super) fn init() {
+    if cpu_features().contains(CpuFeatures::SBI_PMU) {
+        let nr_counters = sbi_call(EID_PMU, FID_NUM_COUNTERS, [0; 5]).unwrap_or(0);
+        // The counter indexes are passed as bitmasks of machine words.
+        NR_COUNTERS.store(nr_counters.min(usize::BITS as usize), Ordering::Relaxed);
+    }
+}
+
+/// Returns whether the hardware performance counters are available.
+pub fn is_available() -> bool {
+    NR_COUNTERS.load(Ordering::Relaxed) != 0
+}
+
+/// The generalized hardware events, which are mapped to the events of the specific hardware
+/// by the SBI firmware.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(usize)]
+pub enum HwEvent {
+    /// The CPU cycles.
+    CpuCycles = 1,
+    /// The retired instructions.
+    Instructions = 2,
+    /// The cache accesses.
+    CacheReferences = 3,
+    /// The cache misses.
+    CacheMisses = 4,
+    /// The retired branch instructions.
+    BranchInstructions = 5,
+    /// The mispredicted branch instructions.
+    BranchMisses = 6,
+    /// The bus cycles.
+    BusCycles = 7,
+    /// The stalled cycles in the frontend.
+    StalledCyclesFrontend = 8,
+    /// The stalled cycles in the backend.
+    StalledCyclesBackend = 9,
+    /// The reference CPU cycles, which are not affected by the frequency scaling.
+    RefCpuCycles = 10,
+}
+
+/// The privilege modes in which the events are not counted.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExcludeModes {
+    /// Whether the events in the user mode are excluded.
+    pub user: bool,
+    /// Whether the events in the supervisor mode (i.e., the kernel) are excluded.
+    pub kernel: bool,
+    /// Whether the events in the machine mode (i.e., the firmware) are excluded.
+    pub firmware: bool,
+}
+
+/// A hardware performance counter that is counting an event on a CPU.
+///
+/// The counter is released once it is stopped by [`PmuCounter::stop`] or dropped.
+#[derive(Debug)]
+pub struct PmuCounter {
+    index: usize,
+    cpu: CpuId,
+    /// The CSR of the counter, or `None` if it is a firmware counter.
+    csr: Option<usize>,
+    /// The mask of the valid bits of the counter.
+    mask: u64,
+}
+
+impl PmuCounter {
+    /// Allocates a counter for the event on the current CPU, and starts it from zero.
+    ///
+    /// It fails if the SBI PMU extension is not available, or if all the counters that can
+    /// count the event are in use.
+    pub fn start(event: HwEvent, exclude: ExcludeModes, guard: &dyn PinCurrentCpu) -> Result<Self> {
+        let nr_counters = NR_COUNTERS.load(Ordering::Relaxed);
+        if nr_counters == 0 {
+            return Err(Error::NotEnoughResources);
+        }
+        let counter_mask = if nr_counters == usize::BITS as usize {
+            usize::MAX
+        } else {
+            (1 << nr_counters) - 1
+        };
+
+        let mut config_flags = CFG_FLAG_CLEAR_VALUE;
+        if exclude.user {
+            config_flags |= CFG_FLAG_SET_UINH;
+        }
+        if exclude.kernel {
+            config_flags |= CFG_FLAG_SET_SINH;
+        }
+        if exclude.firmware {
+            config_flags |= CFG_FLAG_SET_MINH;
+        }
+        // The hardware general events are of type 0, so their indexes are their codes.
+        let index = sbi_call(
+            EID_PMU,
+            FID_COUNTER_CONFIG_MATCHING,
+            [0, counter_mask, config_flags, event as usize, 0],
+        )
+        .map_err(|_| Error::NotEnoughResources)?;
+
+        // The information of a counter consists of its CSR in bits 0..12, its width minus one
+        // in bits 12..18, and whether it is a firmware counter in the bit 63.
+        let info = sbi_call(EID_PMU, FID_COUNTER_GET_INFO, [index, 0, 0, 0, 0]).unwrap_or(0);
+        let (csr, mask) = if info >> 63 == 0 {
+            let width = ((info >> 12) & 0x3f) + 1;
+            let mask = if width >= 64 {
+                u64::MAX
+            } else {
+                (1 << width) - 1
+            };
+            (Some(info & 0xfff), mask)
+        } else {
+            (None, u64::MAX)
+        };
+
+        let counter = Self {
+            index,
+            cpu: guard.current_cpu(),
+            csr,
+            mask,
+        };
+        sbi_call(
+            EID_PMU,
+            FID_COUNTER_START,
+            [index, 1, START_FLAG_SET_INIT_VALUE, 0, 0],
+        )
+        .map_err(|_| Error::NotEnoughResources)?;
+
+        Ok(counter)
+    }
+
+    /// Returns the CPU on which the counter is counting.
+    pub fn cpu(&self) -> CpuId {
+        self.cpu
+    }
+
+    /// Reads the number of the events since the counter is started.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if it is not called on the CPU that started the counter.
+    pub fn read(&self, guard: &dyn PinCurrentCpu) -> u64 {
+        assert_eq!(guard.current_cpu(), self.cpu);
+
+        let value = match self.csr {
+            Some(csr) => read_counter_csr(csr),
+            None => {
+                sbi_call(EID_PMU, FID_COUNTER_FW_READ, [self.index, 0, 0, 0, 0]).unwrap_or(0) as u64
+            }
+        };
+        value & self.mask
+    }
+
+    /// Stops and releases the counter, and returns the number of the events since the
+    /// counter is started.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if it is not called on the CPU that started the counter.
+    pub fn stop(self, guard: &dyn PinCurrentCpu) -> u64 {
+        // The counter is released when it is dropped.
+        self.read(guard)
+    }
+}
+
+impl Drop for PmuCounter {
+    fn drop(&mut self) {
+        // A counter cannot be released on other CPUs, so it is leaked in that case.
+        if crate::cpu::current_cpu_racy() != self.cpu {
+            log::warn!("PMU counter {} is dropped on another CPU", self.index);
+            return;
+        }
+        let _ = sbi_call(
+            EID_PMU,
+            FID_COUNTER_STOP,
+            [self.index, 1, STOP_FLAG_RESET, 0, 0],
+        );
+    }
+}
+
+/// Reads the counter CSR, which must be in `cycle..=hpmcounter31`.
+fn read_counter_csr(csr: usize) -> u64 {
+    macro_rules! read_csr {
+        ($($offset:literal)*) => {
+            match csr - CSR_CYCLE {
+                $(
+                    $offset => {
+                        let value: usize;
+                        // SAFETY: Reading a counter CSR has no side effects. The counter is
+                        // started by the SBI firmware, which allows it to be read in the
+                        // supervisor mode.
+                        unsafe {
+                            asm!("csrr {}, {csr}", out(reg) value, csr = const CSR_CYCLE + $offset)
+                        };
+                        value as u64
+                    }
+                )*
+                _ => 0,
+            }
+        };
+    }
+
+    read_csr!(0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19 20 21 22 23 24 25 26 27 28 29 30 31)
+}
+
+/// Calls the SBI firmware, and returns the value on success or the error code on failure.
+fn sbi_call(eid: usize, fid: usize, args: [usize; 5]) -> core::result::Result<usize, isize> {
+    let error: isize;
+    let value: usize;
+    // SAFETY: The PMU extension of SBI does not access the memory of the kernel.
+    unsafe {
+        asm!(
+            "ecall",
+            inlateout("a0") args[0] => error,
+            inlateout("a1") args[1] => value,
+            in("a2") args[2],
+            in("a3") args[3],
+            in("a4") args[4],
+            in("a6") fid,
+            in("a7") eid,
+        );
+    }
+    if error == 0 {
+        Ok(value)
+    } else {
+        Err(error)
+    }
+}
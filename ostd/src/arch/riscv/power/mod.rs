@@ -0,0 +1,157 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Suspend-to-RAM by the SBI system suspend (SUSP) extension.
+//!
+//! The firmware puts the system into the sleep state and resumes the current hart at the given
+//! physical address with the MMU disabled, as if the hart is booted, while the memory is kept.
+//! So the callee-saved registers and the kernel page table are saved in a [`SuspendContext`]
+//! before suspending, and the resume code enables the boot page table to jump back to the high
+//! addresses, then restores the context, so that [`suspend`] returns as a normal function.
+//!
+//! The supervisor CSRs are also lost in the sleep state, so they are saved and restored
+//! around the call. The other harts must have been stopped, which is required by SBI.
+//!
+//! Reference: <https://github.com/riscv-non-isa/riscv-sbi-doc/blob/master/src/ext-sys-suspend.adoc>
+
+use core::{
+    arch::{asm, global_asm},
+    cell::SyncUnsafeCell,
+};
+
+use crate::{
+    arch::irq,
+    cpu::{cpu_features, CpuFeatures},
+    mm::{kspace::kernel_loaded_offset, Paddr, Vaddr},
+    prelude::*,
+    Error,
+};
+
+global_asm!(include_str!("suspend.S"));
+
+extern "C" {
+    fn __suspend_enter(context: Vaddr, context_paddr: Paddr, resume_entry: Paddr) -> isize;
+    fn __suspend_resume();
+}
+
+/// The SBI error code of the sleep types that are not supported.
+const SBI_ERR_NOT_SUPPORTED: isize = -2;
+
+/// The context that is saved before suspending, whose layout is known by `suspend.S`.
+#[repr(C)]
+struct SuspendContext {
+    ra: usize,
+    sp: usize,
+    gp: usize,
+    tp: usize,
+    s: [usize; 12],
+    satp: usize,
+    /// The virtual address of the context, which is read by the resume code.
+    vaddr: Vaddr,
+}
+
+/// The saved context, which is in the kernel image whose physical address is known.
+static SUSPEND_CONTEXT: SyncUnsafeCell<SuspendContext> = SyncUnsafeCell::new(SuspendContext {
+    ra: 0,
+    sp: 0,
+    gp: 0,
+    tp: 0,
+    s: [0; 12],
+    satp: 0,
+    vaddr: 0,
+});
+
+/// Returns whether the firmware supports suspend-to-RAM.
+pub(crate) fn is_supported() -> bool {
+    cpu_features().contains(CpuFeatures::SBI_SUSP)
+}
+
+/// Suspends the system to RAM, and returns after the system resumes.
+///
+/// # Errors
+///
+/// This function returns [`Error::InvalidArgs`] if the firmware does not support suspend-to-RAM,
+/// or [`Error::IoError`] if it fails to suspend the system.
+///
+/// # Safety
+///
+/// The local IRQs must be disabled, and the other CPUs must have been stopped.
+pub(crate) unsafe fn suspend() -> Result<()> {
+    if !is_supported() {
+        return Err(Error::InvalidArgs);
+    }
+
+    let saved_csrs = SavedCsrs::save();
+
+    let context = SUSPEND_CONTEXT.get() as Vaddr;
+    let context_paddr = context - kernel_loaded_offset();
+    let resume_entry = __suspend_resume as usize - kernel_loaded_offset();
+    // SAFETY: The context is only accessed by the assembly code, and the resume entry restores
+    // the callee-saved registers and the kernel page table from the context, so the function
+    // returns as normal after the system resumes.
+    let error = unsafe { __suspend_enter(context, context_paddr, resume_entry) };
+
+    // SAFETY: The CSRs are restored to the values before suspending.
+    unsafe { saved_csrs.restore() };
+    irq::resume();
+
+    match error {
+        0 => Ok(()),
+        SBI_ERR_NOT_SUPPORTED => Err(Error::InvalidArgs),
+        error => {
+            log::warn!("The SBI firmware fails to suspend the system: {}", error);
+            Err(Error::IoError)
+        }
+    }
+}
+
+/// The supervisor CSRs that are not preserved in the sleep state.
+struct SavedCsrs {
+    sstatus: usize,
+    stvec: usize,
+    sscratch: usize,
+    sie: usize,
+}
+
+impl SavedCsrs {
+    fn save() -> Self {
+        let (sstatus, stvec, sscratch, sie): (usize, usize, usize, usize);
+        // SAFETY: Reading the CSRs has no side effects.
+        unsafe {
+            asm!(
+                "csrr {}, sstatus",
+                "csrr {}, stvec",
+                "csrr {}, sscratch",
+                "csrr {}, sie",
+                out(reg) sstatus,
+                out(reg) stvec,
+                out(reg) sscratch,
+                out(reg) sie,
+            );
+        }
+        Self {
+            sstatus,
+            stvec,
+            sscratch,
+            sie,
+        }
+    }
+
+    /// # Safety
+    ///
+    /// The CSRs must be saved on the current hart before it is suspended.
+    unsafe fn restore(&self) {
+        // SAFETY: The caller ensures that the CSRs are restored to their previous values.
+        unsafe {
+            asm!(
+                "csrw sstatus, {}",
+                "csrw stvec, {}",
+                "csrw sscratch, {}",
+                "csrw sie, {}",
+                in(reg) self.sstatus,
+                in(reg) self.stvec,
+                in(reg) self.sscratch,
+                in(reg) self.sie,
+            );
+        }
+    }
+}
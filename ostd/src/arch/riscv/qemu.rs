@@ -1,6 +1,16 @@
 // SPDX-License-Identifier: MPL-2.0
 
 //! Providing the ability to exit QEMU and return a value as debug result.
+//!
+//! The `virt` machine of QEMU has a SiFive test finisher, whose register makes QEMU exit with
+//! the status written to it. The finisher is preferred, since the SBI implementations may not
+//! pass the reason of the shutdown to QEMU (e.g., OpenSBI always reports a success). If the
+//! finisher is not found in the device tree or not initialized yet, the SBI system reset
+//! extension is used instead.
+
+use spin::Once;
+
+use crate::{arch::boot::DEVICE_TREE, io::MmioRegion, mm::VmIoOnce};
 
 /// The exit code of QEMU.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -11,9 +21,43 @@ pub enum QemuExitCode {
     Failed,
 }
 
+/// The value of the finisher register that makes QEMU exit with zero.
+const FINISHER_PASS: u32 = 0x5555;
+/// The value of the finisher register that makes QEMU exit with the status in the upper half.
+const FINISHER_FAIL: u32 = 0x3333;
+/// The status of a failed exit.
+///
+/// It is the same as the one of `qemu-system-x86_64` with the ISA debug exit device (i.e.,
+/// `(0x20 << 1) | 1`), so the runners that check the status work on both architectures.
+const FAILED_EXIT_STATUS: u32 = 0x41;
+
+static TEST_FINISHER: Once<MmioRegion> = Once::new();
+
+pub(super) fn init() {
+    let Some(node) = DEVICE_TREE.get().unwrap().all_nodes().find(|node| {
+        node.compatible()
+            .is_some_and(|compatible| compatible.all().any(|name| name == "sifive,test0"))
+    }) else {
+        return;
+    };
+    match MmioRegion::claim_fdt_reg(&node) {
+        Ok(region) => {
+            TEST_FINISHER.call_once(|| region);
+        }
+        Err(err) => log::warn!("The MMIO region of the test finisher is not available: {err:?}"),
+    }
+}
+
 /// Exit QEMU with the given exit code.
 pub fn exit_qemu(exit_code: QemuExitCode) -> ! {
     log::debug!("exit qemu with exit code {exit_code:?}");
+    if let Some(finisher) = TEST_FINISHER.get() {
+        let value = match exit_code {
+            QemuExitCode::Success => FINISHER_PASS,
+            QemuExitCode::Failed => (FAILED_EXIT_STATUS << 16) | FINISHER_FAIL,
+        };
+        let _ = finisher.write_once(0, &value);
+    }
     match exit_code {
         QemuExitCode::Success => sbi_rt::system_reset(sbi_rt::Shutdown, sbi_rt::NoReason),
         QemuExitCode::Failed => sbi_rt::system_reset(sbi_rt::Shutdown, sbi_rt::SystemFailure),
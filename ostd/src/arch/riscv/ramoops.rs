@@ -0,0 +1,102 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The RAM region reserved for ramoops.
+//!
+//! Ramoops stores the kernel logs in a RAM region whose content survives warm reboots, so
+//! that the logs of the previous boot (e.g., the panic messages) can be read after the
+//! reboot. Like Linux, the region is described by a `ramoops` node in `/reserved-memory` of
+//! the device tree, which has been excluded from the usable memory when parsing the memory
+//! regions. The layout of the records in the region is managed by the kernel.
+//!
+//! Reference: <https://www.kernel.org/doc/Documentation/devicetree/bindings/reserved-memory/ramoops.yaml>
+
+use spin::Once;
+
+use crate::{
+    arch::boot::DEVICE_TREE,
+    io::IoMem,
+    mm::{
+        page_prop::{CachePolicy, PageFlags},
+        PAGE_SIZE,
+    },
+};
+
+/// The RAM region reserved for ramoops and the sizes of the records in it.
+#[derive(Debug)]
+pub struct RamoopsRegion {
+    /// The I/O memory that maps the whole region
+    pub mem: IoMem,
+    /// The size of each record for the kernel logs dumped on oopses and panics
+    pub record_size: usize,
+    /// The size of the record for the console output
+    pub console_size: usize,
+    /// The size of the record for the function tracer
+    pub ftrace_size: usize,
+    /// The size of the record for the user-space messages
+    pub pmsg_size: usize,
+    /// The size of the ECC of each block in the records, or zero if ECC is disabled
+    pub ecc_size: usize,
+}
+
+static RAMOOPS_REGION: Once<RamoopsRegion> = Once::new();
+
+/// Returns the RAM region reserved for ramoops, if the device tree describes one.
+pub fn ramoops_region() -> Option<&'static RamoopsRegion> {
+    RAMOOPS_REGION.get()
+}
+
+pub(super) fn init() {
+    let Some(node) = DEVICE_TREE
+        .get()
+        .unwrap()
+        .find_node("/reserved-memory")
+        .and_then(|node| {
+            node.children().find(|child| {
+                child
+                    .compatible()
+                    .is_some_and(|compatible| compatible.all().any(|name| name == "ramoops"))
+            })
+        })
+    else {
+        return;
+    };
+
+    let Some(region) = node.reg().and_then(|mut reg| reg.next()) else {
+        log::warn!("The ramoops region has no address");
+        return;
+    };
+    let start = region.starting_address as usize;
+    let size = region.size.unwrap_or(0);
+    if size == 0 || start % PAGE_SIZE != 0 {
+        log::warn!("The ramoops region is empty or not page-aligned");
+        return;
+    }
+
+    let property = |name| {
+        node.property(name)
+            .and_then(|property| property.as_usize())
+            .unwrap_or(0)
+    };
+    // Like Linux, the region is write-combining by default, so that the logs reach the RAM
+    // soon without making the writes too slow.
+    let cache = match property("mem-type") {
+        _ if node.property("unbuffered").is_some() => CachePolicy::Uncacheable,
+        1 => CachePolicy::Uncacheable,
+        2 => CachePolicy::Writeback,
+        _ => CachePolicy::WriteCombining,
+    };
+
+    // SAFETY: The region is reserved memory, which is neither managed by the frame allocator
+    // nor used by others. So the region is only accessed with the `IoMem`, and accessing it
+    // has no side effects except changing its content.
+    let mem = unsafe { IoMem::new(start..start + size, PageFlags::RW, cache) };
+
+    RAMOOPS_REGION.call_once(|| RamoopsRegion {
+        mem,
+        record_size: property("record-size"),
+        console_size: property("console-size"),
+        ftrace_size: property("ftrace-size"),
+        pmsg_size: property("pmsg-size"),
+        ecc_size: property("ecc-size"),
+    });
+}
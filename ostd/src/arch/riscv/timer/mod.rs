@@ -2,11 +2,13 @@
 
 //! The timer support.
 
+pub(super) mod sync;
+
 use core::sync::atomic::{AtomicU64, Ordering};
 
 use spin::Once;
 
-use crate::{arch::boot::DEVICE_TREE, io::IoMem, mm::page_prop::{CachePolicy, PageFlags}};
+use crate::{arch::{acpi, boot::DEVICE_TREE}, io::IoMem, mm::page_prop::{CachePolicy, PageFlags}};
 
 /// The timer frequency (Hz). Here we choose 1000Hz since 1000Hz is easier for unit conversion and
 /// convenient for timer. What's more, the frequency cannot be set too high or too low, 1000Hz is
@@ -22,16 +24,23 @@ pub(crate) static TIMEBASE_FREQ: AtomicU64 = AtomicU64::new(1);
 pub static GOLDFISH_IO_MEM: Once<IoMem> = Once::new();
 
 pub(super) fn init() {
-    let timer_freq = DEVICE_TREE
-        .get()
-        .unwrap()
-        .cpus()
-        .next()
-        .unwrap()
-        .timebase_frequency() as u64;
+    let timer_freq = if acpi::is_enabled() {
+        acpi::rhct::time_base_frequency().expect("the RHCT has no time base frequency")
+    } else {
+        DEVICE_TREE
+            .get()
+            .unwrap()
+            .cpus()
+            .next()
+            .unwrap()
+            .timebase_frequency() as u64
+    };
     TIMEBASE_FREQ.store(timer_freq, Ordering::Relaxed);
 
-    let chosen = DEVICE_TREE.get().unwrap().find_node("/soc/rtc").unwrap();
+    // The RTC is not described in the device tree if ACPI is used.
+    let Some(chosen) = DEVICE_TREE.get().unwrap().find_node("/soc/rtc") else {
+        return;
+    };
     if let Some(compatible) = chosen.compatible()
         && compatible.all().any(|c| c == "google,goldfish-rtc")
     {
@@ -0,0 +1,174 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The synchronization of the `time` CSRs across the harts.
+//!
+//! The `time` CSRs of all the harts should be synchronized by the platform, but some buggy
+//! platforms start the counters of the harts at different moments. Each AP measures the
+//! difference between its `time` CSR and the BSP's when it boots, with a few round trips
+//! through the shared memory (like NTP). If the BSP's time does not fall in the window of a
+//! round trip, the harts are out of sync; a warning is reported and the AP compensates its
+//! time with the measured offset afterwards.
+
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+
+use riscv::register::time;
+
+use crate::cpu_local_cell;
+
+/// The number of the round trips to measure an AP.
+const ROUNDS: u64 = 16;
+
+/// The value of [`SYNC_CPU`] when no AP is being measured.
+const NO_CPU: u32 = u32::MAX;
+/// The value of [`SYNC_SEQ`] when the AP has finished the measurement.
+const SEQ_DONE: u64 = u64::MAX;
+
+/// The ID of the AP that is being measured, or [`NO_CPU`].
+static SYNC_CPU: AtomicU32 = AtomicU32::new(NO_CPU);
+/// The sequence number of the round trips.
+///
+/// The AP starts a round with an odd number, and the BSP answers it with the next even number
+/// after recording its time in [`BSP_TIME`].
+static SYNC_SEQ: AtomicU64 = AtomicU64::new(0);
+/// The time of the BSP in the current round.
+static BSP_TIME: AtomicU64 = AtomicU64::new(0);
+
+/// Whether the time of any hart is compensated.
+static HAS_OFFSET: AtomicBool = AtomicBool::new(false);
+
+cpu_local_cell! {
+    /// The offset that is added to the `time` CSR of the current hart.
+    static TIME_OFFSET: i64 = 0;
+}
+
+/// The largest time that is returned by [`sched_clock`].
+static LAST_SCHED_CLOCK: AtomicU64 = AtomicU64::new(0);
+
+/// Reads the time of the current hart, which is compensated to the time of the BSP.
+pub(crate) fn read_time() -> u64 {
+    if !HAS_OFFSET.load(Ordering::Relaxed) {
+        return time::read64();
+    }
+
+    // The offset and the CSR must belong to the same hart.
+    let _irq_guard = crate::trap::disable_local();
+    (time::read64() as i64).wrapping_add(TIME_OFFSET.load()) as u64
+}
+
+/// Reads the time for the scheduler.
+///
+/// The time never goes backwards, even if the caller migrates to another hart whose time is
+/// still slightly behind after the compensation.
+pub(crate) fn sched_clock() -> u64 {
+    let now = read_time();
+    let last = LAST_SCHED_CLOCK.fetch_max(now, Ordering::Relaxed);
+    now.max(last)
+}
+
+/// A round trip between an AP and the BSP.
+#[derive(Clone, Copy)]
+struct Sample {
+    /// The time of the AP when the round starts.
+    start: u64,
+    /// The time of the BSP.
+    bsp: u64,
+    /// The time of the AP when the round ends.
+    end: u64,
+}
+
+impl Sample {
+    fn round_trip(&self) -> u64 {
+        self.end.wrapping_sub(self.start)
+    }
+
+    /// Returns the offset from the time of the AP to the time of the BSP, assuming that the
+    /// BSP's time is read in the middle of the round trip.
+    fn offset(&self) -> i64 {
+        let middle = self.start.wrapping_add(self.round_trip() / 2);
+        self.bsp.wrapping_sub(middle) as i64
+    }
+
+    /// Returns whether the time of the BSP falls in the round trip.
+    fn is_synchronized(&self) -> bool {
+        (self.start..=self.end).contains(&self.bsp)
+    }
+}
+
+/// Measures the time of the current AP against the BSP, and compensates it if needed.
+///
+/// The BSP must be running [`sync_with_aps`] concurrently.
+pub(crate) fn sync_on_ap(cpu_id: u32) {
+    while SYNC_CPU
+        .compare_exchange(NO_CPU, cpu_id, Ordering::Acquire, Ordering::Relaxed)
+        .is_err()
+    {
+        core::hint::spin_loop();
+    }
+
+    let mut best: Option<Sample> = None;
+    for round in 0..ROUNDS {
+        let seq = round * 2 + 1;
+        let start = time::read64();
+        SYNC_SEQ.store(seq, Ordering::Release);
+        while SYNC_SEQ.load(Ordering::Acquire) != seq + 1 {
+            core::hint::spin_loop();
+        }
+        let end = time::read64();
+
+        let sample = Sample {
+            start,
+            bsp: BSP_TIME.load(Ordering::Relaxed),
+            end,
+        };
+        // The shortest round trip gives the most precise offset.
+        match best {
+            Some(best) if best.round_trip() <= sample.round_trip() => {}
+            _ => best = Some(sample),
+        }
+    }
+    SYNC_SEQ.store(SEQ_DONE, Ordering::Release);
+
+    let best = best.unwrap();
+    if best.is_synchronized() {
+        return;
+    }
+
+    let offset = best.offset();
+    log::warn!(
+        "The time of CPU {} is off by {} ticks (measured within {} ticks), compensating for it",
+        cpu_id,
+        offset,
+        best.round_trip()
+    );
+    TIME_OFFSET.store(offset);
+    HAS_OFFSET.store(true, Ordering::Relaxed);
+}
+
+/// Answers the measurements of `num_aps` APs on the BSP.
+///
+/// The APs are measured one at a time, in the order that they arrive.
+pub(crate) fn sync_with_aps(num_aps: u32) {
+    for _ in 0..num_aps {
+        while SYNC_CPU.load(Ordering::Acquire) == NO_CPU {
+            core::hint::spin_loop();
+        }
+
+        let mut expected = 1;
+        loop {
+            let seq = SYNC_SEQ.load(Ordering::Acquire);
+            if seq == SEQ_DONE {
+                break;
+            }
+            if seq != expected {
+                core::hint::spin_loop();
+                continue;
+            }
+            BSP_TIME.store(time::read64(), Ordering::Relaxed);
+            SYNC_SEQ.store(seq + 1, Ordering::Release);
+            expected += 2;
+        }
+
+        SYNC_SEQ.store(0, Ordering::Relaxed);
+        SYNC_CPU.store(NO_CPU, Ordering::Release);
+    }
+}
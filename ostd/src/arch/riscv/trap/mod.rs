@@ -6,7 +6,7 @@ mod trap;
 
 pub use trap::{GeneralRegs, TrapFrame, UserContext};
 
-use crate::cpu_local_cell;
+use crate::{cpu_local_cell, kallsyms::SymbolizedAddr};
 
 cpu_local_cell! {
     static IS_KERNEL_INTERRUPTED: bool = false;
@@ -27,18 +27,39 @@ pub fn is_kernel_interrupted() -> bool {
 /// Handle traps (only from kernel).
 #[no_mangle]
 extern "C" fn trap_handler(f: &mut TrapFrame) {
-    use riscv::register::scause::Trap;
+    use riscv::register::scause::{Exception, Interrupt, Trap};
 
     match riscv::register::scause::read().cause() {
+        Trap::Interrupt(Interrupt::SupervisorExternal) => {
+            IS_KERNEL_INTERRUPTED.store(true);
+            super::irq::handle_external_interrupt(f);
+            IS_KERNEL_INTERRUPTED.store(false);
+        }
+        Trap::Interrupt(Interrupt::SupervisorSoft) => {
+            IS_KERNEL_INTERRUPTED.store(true);
+            super::irq::handle_software_interrupt(f);
+            IS_KERNEL_INTERRUPTED.store(false);
+        }
         Trap::Interrupt(_) => {
             IS_KERNEL_INTERRUPTED.store(true);
             todo!();
             IS_KERNEL_INTERRUPTED.store(false);
         }
+        Trap::Exception(Exception::Breakpoint) if super::kprobe::handle_breakpoint(f) => {}
         Trap::Exception(e) => {
             let stval = riscv::register::stval::read();
+            if matches!(
+                e,
+                Exception::LoadFault
+                    | Exception::StoreFault
+                    | Exception::LoadPageFault
+                    | Exception::StorePageFault
+            ) {
+                super::mm::firmware::report_fault(stval);
+            }
             panic!(
-                "Cannot handle kernel cpu exception: {e:?}. stval: {stval:#x}, trapframe: {f:#x?}.",
+                "Cannot handle kernel cpu exception: {e:?} at {}. stval: {stval:#x}, trapframe: {f:#x?}.",
+                SymbolizedAddr(f.sepc),
             );
         }
     }
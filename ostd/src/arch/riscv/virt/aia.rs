@@ -0,0 +1,171 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The guest interrupt files of the IMSICs in the advanced interrupt architecture (AIA).
+//!
+//! With AIA, each hart has an IMSIC, which has an interrupt file for the supervisor mode and
+//! some guest interrupt files. If `hstatus.VGEIN` selects a guest interrupt file, the external
+//! interrupts of the VS mode come from the file directly, so an MSI is delivered to the guest
+//! by writing to the file, without the host injecting the interrupt.
+//!
+//! The IMSICs are described by the device tree. The interrupt files of the harts are assumed
+//! to be placed in the order of the CPU IDs, and only the first group of the IMSICs is used.
+//!
+//! Reference: <https://github.com/riscv/riscv-aia>
+
+use spin::Once;
+
+use crate::{
+    arch::boot::DEVICE_TREE,
+    cpu::{CpuId, PinCurrentCpu},
+    io::IoMem,
+    mm::{Paddr, VmIoOnce, PAGE_SIZE},
+    prelude::*,
+    sync::SpinLock,
+    task::disable_preempt,
+    Error,
+};
+
+const CSR_HGEIP: usize = 0xE12;
+
+/// The interrupt number of the supervisor external interrupts, to which the supervisor-level
+/// IMSICs are connected.
+const IRQ_S_EXT: u32 = 9;
+
+/// The offset of `seteipnum_le` in an interrupt file, writing to which sends an MSI.
+const SETEIPNUM_LE_OFFSET: usize = 0;
+
+struct Imsics {
+    base: Paddr,
+    nr_harts: usize,
+    guest_index_bits: u32,
+    /// The bitmaps of the allocated guest interrupt files of the harts.
+    allocated: SpinLock<Vec<u64>>,
+}
+
+static IMSICS: Once<Imsics> = Once::new();
+
+pub(super) fn init() {
+    let Some(node) = DEVICE_TREE.get().unwrap().all_nodes().find(|node| {
+        node.compatible()
+            .is_some_and(|compatible| compatible.all().any(|name| name == "riscv,imsics"))
+            && node
+                .property("interrupts-extended")
+                .and_then(|prop| prop.value.get(4..8))
+                .is_some_and(|irq| u32::from_be_bytes(irq.try_into().unwrap()) == IRQ_S_EXT)
+    }) else {
+        return;
+    };
+
+    let guest_index_bits = node
+        .property("riscv,guest-index-bits")
+        .and_then(|prop| prop.as_usize())
+        .unwrap_or(0) as u32;
+    // The index of a guest interrupt file is in `hstatus.VGEIN`, which is of 6 bits.
+    if guest_index_bits == 0 || guest_index_bits > 6 {
+        return;
+    }
+    let Some(region) = node.reg().and_then(|mut reg| reg.next()) else {
+        return;
+    };
+
+    // Each entry of `interrupts-extended` is a pair of the phandle and the interrupt number.
+    let nr_entries = node
+        .property("interrupts-extended")
+        .map_or(0, |prop| prop.value.len() / 8);
+    let stride = PAGE_SIZE << guest_index_bits;
+    let nr_harts = nr_entries.min(region.size.unwrap_or(0) / stride);
+
+    IMSICS.call_once(|| Imsics {
+        base: region.starting_address as Paddr,
+        nr_harts,
+        guest_index_bits,
+        allocated: SpinLock::new(alloc::vec![0; nr_harts]),
+    });
+}
+
+/// Returns the number of the guest interrupt files of each CPU.
+///
+/// It returns zero if the guest interrupt files are not available.
+pub fn nr_guest_files() -> usize {
+    IMSICS
+        .get()
+        .map_or(0, |imsics| (1 << imsics.guest_index_bits) - 1)
+}
+
+/// A guest interrupt file of the IMSIC of a CPU.
+///
+/// The file only delivers the interrupts to a guest that runs on the same CPU (see
+/// [`GuestContext::set_interrupt_file`]).
+///
+/// [`GuestContext::set_interrupt_file`]: super::GuestContext::set_interrupt_file
+#[derive(Debug)]
+pub struct GuestInterruptFile {
+    cpu: CpuId,
+    /// The index of the file, which starts from 1 since the supervisor file is the 0th one.
+    index: usize,
+    mem: IoMem,
+}
+
+impl GuestInterruptFile {
+    /// Allocates a free guest interrupt file of the CPU.
+    pub fn alloc(cpu: CpuId) -> Result<Self> {
+        let Some(imsics) = IMSICS.get() else {
+            return Err(Error::InvalidArgs);
+        };
+        if cpu.as_usize() >= imsics.nr_harts {
+            return Err(Error::InvalidArgs);
+        }
+
+        let mut allocated = imsics.allocated.lock();
+        let bitmap = &mut allocated[cpu.as_usize()];
+        let Some(index) = (1..=nr_guest_files()).find(|index| *bitmap & (1 << index) == 0) else {
+            return Err(Error::NotEnoughResources);
+        };
+
+        let paddr = imsics.base
+            + (cpu.as_usize() << (imsics.guest_index_bits as usize)) * PAGE_SIZE
+            + index * PAGE_SIZE;
+        let mem = IoMem::acquire(paddr..paddr + PAGE_SIZE)?;
+        *bitmap |= 1 << index;
+
+        Ok(Self { cpu, index, mem })
+    }
+
+    /// Returns the CPU of the file.
+    pub fn cpu(&self) -> CpuId {
+        self.cpu
+    }
+
+    /// Returns the physical address of the file, which is mapped to the guest as its IMSIC.
+    pub fn paddr(&self) -> Paddr {
+        self.mem.paddr()
+    }
+
+    /// Returns the index of the file, which is the value of `hstatus.VGEIN` that selects it.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Sends an MSI with the interrupt identity to the file.
+    pub fn send(&self, eid: u32) -> Result<()> {
+        self.mem.write_once(SETEIPNUM_LE_OFFSET, &eid.to_le())
+    }
+
+    /// Returns whether the file has a pending and enabled interrupt.
+    ///
+    /// This method must be called on the CPU of the file, otherwise it returns `false`.
+    pub fn is_pending(&self) -> bool {
+        let preempt_guard = disable_preempt();
+        if preempt_guard.current_cpu() != self.cpu {
+            return false;
+        }
+        read_csr!(CSR_HGEIP) & (1 << self.index) != 0
+    }
+}
+
+impl Drop for GuestInterruptFile {
+    fn drop(&mut self) {
+        let imsics = IMSICS.get().unwrap();
+        imsics.allocated.lock()[self.cpu.as_usize()] &= !(1 << self.index);
+    }
+}
@@ -0,0 +1,354 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The context of a virtual CPU.
+
+use core::arch::{asm, global_asm};
+
+use super::{aia::GuestInterruptFile, exit::VmExit, gstage::GuestPageTable};
+use crate::{
+    arch::{cpu::context::FpuState, trap::GeneralRegs},
+    cpu::{CpuId, PinCurrentCpu},
+    cpu_local_cell,
+    task::disable_preempt,
+    trap::disable_local,
+};
+
+global_asm!(include_str!("guest.S"));
+
+extern "C" {
+    fn __guest_run(regs: *mut GuestRegs);
+}
+
+const CSR_SSTATUS: usize = 0x100;
+const CSR_SCAUSE: usize = 0x142;
+const CSR_STVAL: usize = 0x143;
+
+const CSR_HEDELEG: usize = 0x602;
+const CSR_HIDELEG: usize = 0x603;
+const CSR_HTIMEDELTA: usize = 0x605;
+const CSR_HCOUNTEREN: usize = 0x606;
+const CSR_HTVAL: usize = 0x643;
+const CSR_HVIP: usize = 0x645;
+const CSR_HTINST: usize = 0x64A;
+const CSR_HGATP: usize = 0x680;
+
+const CSR_VSSTATUS: usize = 0x200;
+const CSR_VSIE: usize = 0x204;
+const CSR_VSTVEC: usize = 0x205;
+const CSR_VSSCRATCH: usize = 0x240;
+const CSR_VSEPC: usize = 0x241;
+const CSR_VSCAUSE: usize = 0x242;
+const CSR_VSTVAL: usize = 0x243;
+const CSR_VSATP: usize = 0x280;
+
+const SSTATUS_SIE: usize = 1 << 1;
+const SSTATUS_SPIE: usize = 1 << 5;
+const SSTATUS_SPP: usize = 1 << 8;
+const SSTATUS_FS_DIRTY: usize = 3 << 13;
+const SSTATUS_UXL_64: usize = 2 << 32;
+
+/// The previous mode is virtualized, so `sret` enters the guest.
+const HSTATUS_SPV: usize = 1 << 7;
+/// The guest kernel accesses its memory with the hypervisor load/store instructions as the VS
+/// mode.
+const HSTATUS_SPVP: usize = 1 << 8;
+/// `wfi` in the guest raises a virtual instruction exception, so the host can block the vCPU.
+const HSTATUS_VTW: usize = 1 << 21;
+/// The VS mode is of 64 bits.
+const HSTATUS_VSXL_64: usize = 2 << 32;
+/// The guest interrupt file that provides the VS-level external interrupts.
+const HSTATUS_VGEIN_SHIFT: usize = 12;
+const HSTATUS_VGEIN_MASK: usize = 0x3f << HSTATUS_VGEIN_SHIFT;
+
+/// The exceptions that are delegated to the guest: the misaligned instructions, the
+/// breakpoints, the system calls of the guest user programs, and the page faults.
+const HEDELEG_BITS: usize = (1 << 0) | (1 << 3) | (1 << 8) | (1 << 12) | (1 << 13) | (1 << 15);
+/// The interrupts that are delegated to the guest: the VS-level software, timer and external
+/// interrupts.
+const HIDELEG_BITS: usize = (1 << 2) | (1 << 6) | (1 << 10);
+
+cpu_local_cell! {
+    /// The generation of the G-stage page table whose TLB entries are last flushed on the CPU.
+    static LAST_GSTAGE_GENERATION: usize = 0;
+}
+
+/// The registers that are saved and restored by the assembly code.
+#[derive(Debug, Default, Clone, Copy)]
+#[repr(C)]
+struct GuestRegs {
+    general: GeneralRegs,
+    sstatus: usize,
+    sepc: usize,
+    hstatus: usize,
+}
+
+/// The VS CSRs, which are the supervisor CSRs seen by the guest kernel.
+#[derive(Debug, Default, Clone, Copy)]
+#[expect(missing_docs)]
+pub struct VsCsrs {
+    pub vsstatus: usize,
+    pub vsie: usize,
+    pub vstvec: usize,
+    pub vsscratch: usize,
+    pub vsepc: usize,
+    pub vscause: usize,
+    pub vstval: usize,
+    pub vsatp: usize,
+}
+
+/// The interrupts that can be injected into the guest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(usize)]
+pub enum VirtualInterrupt {
+    /// The supervisor software interrupt.
+    Software = 2,
+    /// The supervisor timer interrupt.
+    Timer = 6,
+    /// The supervisor external interrupt.
+    External = 10,
+}
+
+/// The context of a virtual CPU, with which the guest runs in the VS mode.
+#[derive(Debug)]
+pub struct GuestContext {
+    regs: GuestRegs,
+    vs_csrs: VsCsrs,
+    fpu_state: FpuState,
+    hvip: usize,
+    htimedelta: usize,
+    /// The CPU and the index of the guest interrupt file.
+    interrupt_file: Option<(CpuId, usize)>,
+    is_tlb_flush_requested: bool,
+    is_icache_sync_requested: bool,
+}
+
+impl Default for GuestContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GuestContext {
+    /// Creates a context whose guest starts in the VS mode at address zero.
+    pub fn new() -> Self {
+        Self {
+            regs: GuestRegs {
+                hstatus: HSTATUS_SPV | HSTATUS_SPVP | HSTATUS_VTW | HSTATUS_VSXL_64,
+                ..Default::default()
+            },
+            vs_csrs: VsCsrs {
+                vsstatus: SSTATUS_UXL_64,
+                ..Default::default()
+            },
+            fpu_state: FpuState::default(),
+            hvip: 0,
+            htimedelta: 0,
+            interrupt_file: None,
+            is_tlb_flush_requested: false,
+            is_icache_sync_requested: false,
+        }
+    }
+
+    /// Returns a reference to the general registers.
+    pub fn general_regs(&self) -> &GeneralRegs {
+        &self.regs.general
+    }
+
+    /// Returns a mutable reference to the general registers.
+    pub fn general_regs_mut(&mut self) -> &mut GeneralRegs {
+        &mut self.regs.general
+    }
+
+    /// Returns the program counter.
+    pub fn pc(&self) -> usize {
+        self.regs.sepc
+    }
+
+    /// Sets the program counter.
+    pub fn set_pc(&mut self, pc: usize) {
+        self.regs.sepc = pc;
+    }
+
+    /// Returns a reference to the VS CSRs.
+    pub fn vs_csrs(&self) -> &VsCsrs {
+        &self.vs_csrs
+    }
+
+    /// Returns a mutable reference to the VS CSRs.
+    pub fn vs_csrs_mut(&mut self) -> &mut VsCsrs {
+        &mut self.vs_csrs
+    }
+
+    /// Returns a reference to the FPU state.
+    pub fn fpu_state(&self) -> &FpuState {
+        &self.fpu_state
+    }
+
+    /// Returns a mutable reference to the FPU state.
+    pub fn fpu_state_mut(&mut self) -> &mut FpuState {
+        &mut self.fpu_state
+    }
+
+    /// Sets or clears a pending interrupt of the guest.
+    ///
+    /// The guest can clear the pending software interrupt by itself, which is seen after the
+    /// guest exits.
+    pub fn set_interrupt_pending(&mut self, irq: VirtualInterrupt, is_pending: bool) {
+        if is_pending {
+            self.hvip |= 1 << irq as usize;
+        } else {
+            self.hvip &= !(1 << irq as usize);
+        }
+    }
+
+    /// Returns whether an interrupt of the guest is pending.
+    pub fn is_interrupt_pending(&self, irq: VirtualInterrupt) -> bool {
+        self.hvip & (1 << irq as usize) != 0
+    }
+
+    /// Sets the difference between the time of the guest and that of the host, in the ticks
+    /// of the `time` CSR.
+    pub fn set_time_delta(&mut self, delta: usize) {
+        self.htimedelta = delta;
+    }
+
+    /// Returns the difference between the time of the guest and that of the host.
+    pub fn time_delta(&self) -> usize {
+        self.htimedelta
+    }
+
+    /// Sets the guest interrupt file, from which the guest receives the external interrupts.
+    ///
+    /// The file can only deliver the interrupts when the guest runs on the CPU of the file.
+    /// On the other CPUs, the guest runs without the file, and the interrupts in the file are
+    /// delivered after the guest runs on the CPU again.
+    pub fn set_interrupt_file(&mut self, file: Option<&GuestInterruptFile>) {
+        self.interrupt_file = file.map(|file| (file.cpu(), file.index()));
+    }
+
+    /// Requests that the VS-stage TLB entries be flushed before the guest runs again, which is
+    /// needed after the guest asks for a remote `sfence.vma`.
+    pub fn request_tlb_flush(&mut self) {
+        self.is_tlb_flush_requested = true;
+    }
+
+    /// Requests that the instruction cache be synchronized before the guest runs again, which
+    /// is needed after the guest asks for a remote `fence.i`.
+    pub fn request_icache_sync(&mut self) {
+        self.is_icache_sync_requested = true;
+    }
+
+    /// Runs the guest with the G-stage page table, and returns when the guest exits.
+    ///
+    /// If the guest calls the SBI, the program counter is moved past the `ecall` instruction
+    /// before this method returns.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the H extension is not available (see [`super::is_supported`]).
+    pub fn run(&mut self, page_table: &GuestPageTable) -> VmExit {
+        assert!(super::is_supported());
+
+        // The guest and the host share the CSRs of the CPU, so the guest must not be
+        // interrupted or migrated until the host CSRs are restored.
+        let preempt_guard = disable_preempt();
+        let _irq_guard = disable_local();
+
+        let host_sstatus = read_csr!(CSR_SSTATUS);
+        write_csr!(CSR_SSTATUS, host_sstatus | SSTATUS_FS_DIRTY);
+        let host_fpu_state = FpuState::default();
+        host_fpu_state.save();
+        self.fpu_state.restore();
+
+        // `sret` enters the VS mode with the interrupts of the host disabled until it returns
+        // to the host, and the interrupts of the guest are controlled by `vsstatus`.
+        self.regs.sstatus =
+            (host_sstatus | SSTATUS_SPP | SSTATUS_FS_DIRTY) & !(SSTATUS_SIE | SSTATUS_SPIE);
+        let vgein = match self.interrupt_file {
+            Some((cpu, index)) if cpu == preempt_guard.current_cpu() => index,
+            _ => 0,
+        };
+        self.regs.hstatus = (self.regs.hstatus & !HSTATUS_VGEIN_MASK)
+            | HSTATUS_SPV
+            | (vgein << HSTATUS_VGEIN_SHIFT);
+
+        // The delegations are set on every entry, so that they need not be initialized on
+        // each CPU.
+        write_csr!(CSR_HEDELEG, HEDELEG_BITS);
+        write_csr!(CSR_HIDELEG, HIDELEG_BITS);
+        write_csr!(CSR_HCOUNTEREN, usize::MAX);
+        write_csr!(CSR_HVIP, self.hvip);
+        write_csr!(CSR_HTIMEDELTA, self.htimedelta);
+        self.write_vs_csrs();
+
+        write_csr!(CSR_HGATP, page_table.hgatp());
+        let generation = page_table.generation();
+        if LAST_GSTAGE_GENERATION.load() != generation {
+            // All the guests use VMID 0, so the TLB entries of both stages are flushed.
+            // SAFETY: Flushing the TLB entries of the guests does not affect the host.
+            unsafe {
+                // `hfence.gvma zero, zero` and `hfence.vvma zero, zero`.
+                asm!(".word 0x62000073", ".word 0x22000073");
+            }
+            LAST_GSTAGE_GENERATION.store(generation);
+        } else if self.is_tlb_flush_requested {
+            // SAFETY: Flushing the TLB entries of the guests does not affect the host.
+            unsafe { asm!(".word 0x22000073") };
+        }
+        if self.is_icache_sync_requested {
+            // SAFETY: `fence.i` only synchronizes the instruction cache.
+            unsafe { asm!("fence.i") };
+        }
+        self.is_tlb_flush_requested = false;
+        self.is_icache_sync_requested = false;
+
+        // SAFETY: The trap vector is switched to the VM exit code by `__guest_run`, which
+        // saves the guest registers and restores the host registers and the trap vector
+        // before it returns. The interrupts are disabled, so no traps of the host occur in
+        // between.
+        unsafe { __guest_run(&mut self.regs) };
+        self.regs.general.zero = 0;
+
+        let scause = read_csr!(CSR_SCAUSE);
+        let stval = read_csr!(CSR_STVAL);
+        let htval = read_csr!(CSR_HTVAL);
+        let htinst = read_csr!(CSR_HTINST);
+        self.hvip = read_csr!(CSR_HVIP);
+        self.read_vs_csrs();
+        write_csr!(CSR_HGATP, 0);
+
+        self.fpu_state.save();
+        host_fpu_state.restore();
+        write_csr!(CSR_SSTATUS, host_sstatus);
+
+        let exit = VmExit::decode(scause, stval, htval, htinst);
+        if exit == VmExit::SbiCall {
+            self.regs.sepc += 4;
+        }
+        exit
+    }
+
+    fn write_vs_csrs(&self) {
+        let csrs = &self.vs_csrs;
+        write_csr!(CSR_VSSTATUS, csrs.vsstatus);
+        write_csr!(CSR_VSIE, csrs.vsie);
+        write_csr!(CSR_VSTVEC, csrs.vstvec);
+        write_csr!(CSR_VSSCRATCH, csrs.vsscratch);
+        write_csr!(CSR_VSEPC, csrs.vsepc);
+        write_csr!(CSR_VSCAUSE, csrs.vscause);
+        write_csr!(CSR_VSTVAL, csrs.vstval);
+        write_csr!(CSR_VSATP, csrs.vsatp);
+    }
+
+    fn read_vs_csrs(&mut self) {
+        let csrs = &mut self.vs_csrs;
+        csrs.vsstatus = read_csr!(CSR_VSSTATUS);
+        csrs.vsie = read_csr!(CSR_VSIE);
+        csrs.vstvec = read_csr!(CSR_VSTVEC);
+        csrs.vsscratch = read_csr!(CSR_VSSCRATCH);
+        csrs.vsepc = read_csr!(CSR_VSEPC);
+        csrs.vscause = read_csr!(CSR_VSCAUSE);
+        csrs.vstval = read_csr!(CSR_VSTVAL);
+        csrs.vsatp = read_csr!(CSR_VSATP);
+    }
+}
@@ -0,0 +1,195 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The VM exits, i.e., the traps from the guest that are handled by the host.
+
+use crate::arch::trap::GeneralRegs;
+
+/// The interrupt bit of `scause`.
+const SCAUSE_INTERRUPT: usize = 1 << (usize::BITS - 1);
+
+const CAUSE_VIRTUAL_SUPERVISOR_ECALL: usize = 10;
+const CAUSE_INSTRUCTION_GUEST_PAGE_FAULT: usize = 20;
+const CAUSE_LOAD_GUEST_PAGE_FAULT: usize = 21;
+const CAUSE_VIRTUAL_INSTRUCTION: usize = 22;
+const CAUSE_STORE_GUEST_PAGE_FAULT: usize = 23;
+
+/// The reason why the guest exits to the host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VmExit {
+    /// The guest kernel calls the SBI (i.e., an `ecall` in the VS mode).
+    ///
+    /// The extension ID and the function ID are in `a7` and `a6`, and the arguments are in
+    /// `a0`-`a5`. The program counter has been moved past the `ecall` instruction.
+    SbiCall,
+    /// The guest accesses a guest physical address that is not mapped by the G-stage page
+    /// table, or is mapped without the required permissions.
+    GuestPageFault {
+        /// The guest physical address.
+        gpa: usize,
+        /// The kind of the access.
+        access: GuestAccess,
+        /// The decoded load or store instruction, which is used to emulate the MMIO access.
+        ///
+        /// It is `None` if the hardware does not provide the trapped instruction in `htinst`.
+        mmio: Option<MmioInsn>,
+    },
+    /// The guest executes an instruction that must be emulated by the host (e.g., `wfi` with
+    /// `hstatus.VTW` set).
+    VirtualInstruction {
+        /// The instruction, or zero if the hardware does not provide it.
+        insn: usize,
+    },
+    /// An interrupt of the host occurs while the guest is running.
+    Interrupt {
+        /// The interrupt cause (e.g., 5 for the supervisor timer interrupt).
+        cause: usize,
+    },
+    /// Other exceptions, which are not delegated to the guest.
+    Exception {
+        /// The value of `scause`.
+        scause: usize,
+        /// The value of `stval`.
+        stval: usize,
+    },
+}
+
+/// The kind of an access to the guest physical memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GuestAccess {
+    /// Reading.
+    Read,
+    /// Writing.
+    Write,
+    /// Executing.
+    Exec,
+}
+
+/// A decoded load or store instruction that traps on a guest page fault.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MmioInsn {
+    /// The width of the access in bytes.
+    pub width: usize,
+    /// The index of the destination register of a load or the source register of a store.
+    pub reg: usize,
+    /// Whether the instruction is a store.
+    pub is_write: bool,
+    /// Whether the loaded value is sign-extended.
+    pub sign_extend: bool,
+    /// The length of the original instruction in bytes, by which the program counter should
+    /// be moved after the access is emulated.
+    pub len: usize,
+}
+
+impl MmioInsn {
+    /// Decodes the transformed instruction in `htinst`.
+    fn decode(htinst: usize) -> Option<Self> {
+        // A transformed instruction has bit 0 set, and bit 1 tells whether the original
+        // instruction is of 32 bits or compressed.
+        if htinst & 1 == 0 {
+            return None;
+        }
+        let len = if htinst & 2 != 0 { 4 } else { 2 };
+        let opcode = (htinst & 0x7f) | 2;
+        let funct3 = (htinst >> 12) & 0x7;
+
+        let (is_write, reg) = match opcode {
+            0x03 => (false, (htinst >> 7) & 0x1f),
+            0x23 => (true, (htinst >> 20) & 0x1f),
+            _ => return None,
+        };
+        let (width, sign_extend) = match (is_write, funct3) {
+            (_, 0) => (1, true),
+            (_, 1) => (2, true),
+            (_, 2) => (4, true),
+            (_, 3) => (8, false),
+            (false, 4) => (1, false),
+            (false, 5) => (2, false),
+            (false, 6) => (4, false),
+            _ => return None,
+        };
+
+        Some(Self {
+            width,
+            reg,
+            is_write,
+            sign_extend: sign_extend && !is_write,
+            len,
+        })
+    }
+
+    /// Returns the value to be stored, which is truncated to the width.
+    pub fn store_value(&self, regs: &GeneralRegs) -> u64 {
+        let value = read_reg(regs, self.reg) as u64;
+        if self.width == 8 {
+            value
+        } else {
+            value & ((1 << (self.width * 8)) - 1)
+        }
+    }
+
+    /// Writes the loaded value to the destination register, extending it as the instruction
+    /// does.
+    pub fn complete_load(&self, regs: &mut GeneralRegs, value: u64) {
+        let shift = 64 - self.width * 8;
+        let value = if self.sign_extend {
+            ((value << shift) as i64 >> shift) as u64
+        } else if self.width == 8 {
+            value
+        } else {
+            value & ((1 << (self.width * 8)) - 1)
+        };
+        write_reg(regs, self.reg, value as usize);
+    }
+}
+
+fn read_reg(regs: &GeneralRegs, index: usize) -> usize {
+    assert!(index < 32);
+    // SAFETY: `GeneralRegs` is `repr(C)` and consists of 32 `usize`s.
+    unsafe {
+        (regs as *const GeneralRegs as *const usize)
+            .add(index)
+            .read()
+    }
+}
+
+fn write_reg(regs: &mut GeneralRegs, index: usize, value: usize) {
+    assert!(index < 32);
+    // Writing to `zero` has no effect.
+    if index == 0 {
+        return;
+    }
+    // SAFETY: `GeneralRegs` is `repr(C)` and consists of 32 `usize`s.
+    unsafe {
+        (regs as *mut GeneralRegs as *mut usize)
+            .add(index)
+            .write(value)
+    }
+}
+
+impl VmExit {
+    /// Decodes the VM exit from the trap CSRs.
+    pub(super) fn decode(scause: usize, stval: usize, htval: usize, htinst: usize) -> Self {
+        if scause & SCAUSE_INTERRUPT != 0 {
+            return Self::Interrupt {
+                cause: scause & !SCAUSE_INTERRUPT,
+            };
+        }
+
+        let access = match scause {
+            CAUSE_VIRTUAL_SUPERVISOR_ECALL => return Self::SbiCall,
+            CAUSE_VIRTUAL_INSTRUCTION => return Self::VirtualInstruction { insn: stval },
+            CAUSE_INSTRUCTION_GUEST_PAGE_FAULT => GuestAccess::Exec,
+            CAUSE_LOAD_GUEST_PAGE_FAULT => GuestAccess::Read,
+            CAUSE_STORE_GUEST_PAGE_FAULT => GuestAccess::Write,
+            _ => return Self::Exception { scause, stval },
+        };
+        // `htval` holds the guest physical address shifted right by 2 bits, and the lowest
+        // bits are the same as those of the guest virtual address in `stval`.
+        let gpa = (htval << 2) | (stval & 0x3);
+        let mmio = match access {
+            GuestAccess::Exec => None,
+            GuestAccess::Read | GuestAccess::Write => MmioInsn::decode(htinst),
+        };
+        Self::GuestPageFault { gpa, access, mmio }
+    }
+}
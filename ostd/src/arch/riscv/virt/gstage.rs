@@ -0,0 +1,265 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The G-stage page table, which translates the guest physical addresses.
+
+use alloc::collections::BTreeMap;
+use core::{
+    mem::size_of,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use align_ext::AlignExt;
+use bitflags::bitflags;
+
+use super::aia::GuestInterruptFile;
+use crate::{
+    arch::mm::PageTableFlags,
+    mm::{paddr_to_vaddr, Frame, FrameAllocOptions, Segment, UFrame, PAGE_SIZE},
+    prelude::*,
+    sync::SpinLock,
+    Error,
+};
+
+/// The number of bits of the guest physical addresses in the Sv39x4 mode.
+const GPA_BITS: usize = 41;
+/// The number of the entries in the root table, which is 4 times as many as that of the
+/// other tables in the Sv39x4 mode.
+const NR_ROOT_ENTRIES: usize = 2048;
+const NR_ENTRIES: usize = 512;
+const ROOT_SIZE: usize = NR_ROOT_ENTRIES * size_of::<u64>();
+
+/// The mode field of `hgatp` for Sv39x4.
+const HGATP_MODE_SV39X4: usize = 8 << 60;
+
+/// The generation of the G-stage page tables, which is bumped whenever a table is modified.
+///
+/// All the tables use VMID 0, so a CPU must flush the G-stage TLB entries if it switches to a
+/// different table or the table has been modified since the CPU last flushed them.
+static NEXT_GENERATION: AtomicUsize = AtomicUsize::new(1);
+
+bitflags! {
+    /// The permissions of a guest physical page.
+    pub struct GuestPerms: u8 {
+        /// The page can be read.
+        const READ  = 1 << 0;
+        /// The page can be written.
+        const WRITE = 1 << 1;
+        /// The page can be executed.
+        const EXEC  = 1 << 2;
+    }
+}
+
+/// A G-stage page table in the Sv39x4 mode, which maps the guest physical pages to the host
+/// frames.
+///
+/// The mapped frames and guest interrupt files are kept alive by the table until they are
+/// unmapped or the table is dropped.
+pub struct GuestPageTable {
+    inner: SpinLock<Inner>,
+    generation: AtomicUsize,
+}
+
+struct Inner {
+    /// The root table, which is of 16 KiB and aligned to 16 KiB.
+    root: Segment<()>,
+    /// The non-root tables.
+    tables: Vec<Frame<()>>,
+    /// The mapped frames, indexed by the guest physical addresses.
+    frames: BTreeMap<usize, UFrame>,
+    /// The mapped guest interrupt files, which are kept allocated while they are mapped.
+    interrupt_files: BTreeMap<usize, Arc<GuestInterruptFile>>,
+}
+
+impl GuestPageTable {
+    /// Creates an empty table.
+    pub fn new() -> Result<Self> {
+        // The root table must be aligned to its size, so more frames are allocated to find
+        // an aligned range in them, and the other frames are freed.
+        let nr_frames = ROOT_SIZE / PAGE_SIZE;
+        let segment = FrameAllocOptions::new().alloc_segment(nr_frames * 2 - 1)?;
+        let offset = segment.start_paddr().align_up(ROOT_SIZE) - segment.start_paddr();
+        let segment = if offset == 0 {
+            segment
+        } else {
+            segment.split(offset).1
+        };
+        let root = if segment.size() == ROOT_SIZE {
+            segment
+        } else {
+            segment.split(ROOT_SIZE).0
+        };
+
+        Ok(Self {
+            inner: SpinLock::new(Inner {
+                root,
+                tables: Vec::new(),
+                frames: BTreeMap::new(),
+                interrupt_files: BTreeMap::new(),
+            }),
+            generation: AtomicUsize::new(NEXT_GENERATION.fetch_add(1, Ordering::Relaxed)),
+        })
+    }
+
+    /// Maps a guest physical page to a frame.
+    ///
+    /// The previous mapping of the page, if any, is replaced.
+    pub fn map(&self, gpa: usize, frame: UFrame, perms: GuestPerms) -> Result<()> {
+        check_gpa(gpa)?;
+        // The writable pages must also be readable, as the encoding of the PTEs requires.
+        if perms.is_empty()
+            || (perms.contains(GuestPerms::WRITE) && !perms.contains(GuestPerms::READ))
+        {
+            return Err(Error::InvalidArgs);
+        }
+
+        let mut inner = self.inner.lock();
+        let pte = inner.leaf_pte(gpa, true)?.unwrap();
+        // The accessed and dirty bits are set in advance, so that the hardware that does not
+        // update them never raises the guest page faults for them. The G-stage leaves must
+        // always be marked as user pages.
+        let mut flags = PageTableFlags::VALID
+            | PageTableFlags::USER
+            | PageTableFlags::ACCESSED
+            | PageTableFlags::DIRTY;
+        if perms.contains(GuestPerms::READ) {
+            flags |= PageTableFlags::READABLE;
+        }
+        if perms.contains(GuestPerms::WRITE) {
+            flags |= PageTableFlags::WRITABLE;
+        }
+        if perms.contains(GuestPerms::EXEC) {
+            flags |= PageTableFlags::EXECUTABLE;
+        }
+        // SAFETY: The PTE is in a table owned by `inner`.
+        unsafe { pte.write(make_pte(frame.start_paddr(), flags)) };
+        inner.interrupt_files.remove(&gpa);
+        inner.frames.insert(gpa, frame);
+        drop(inner);
+
+        self.bump_generation();
+        Ok(())
+    }
+
+    /// Unmaps a guest physical page, and returns the frame that is mapped to it.
+    pub fn unmap(&self, gpa: usize) -> Result<Option<UFrame>> {
+        check_gpa(gpa)?;
+
+        let mut inner = self.inner.lock();
+        let Some(pte) = inner.leaf_pte(gpa, false)? else {
+            return Ok(None);
+        };
+        // SAFETY: The PTE is in a table owned by `inner`.
+        unsafe { pte.write(0) };
+        let frame = inner.frames.remove(&gpa);
+        inner.interrupt_files.remove(&gpa);
+        drop(inner);
+
+        self.bump_generation();
+        Ok(frame)
+    }
+
+    /// Maps a guest physical page to a guest interrupt file, which becomes the IMSIC of the
+    /// guest.
+    ///
+    /// The previous mapping of the page, if any, is replaced.
+    pub fn map_interrupt_file(&self, gpa: usize, file: Arc<GuestInterruptFile>) -> Result<()> {
+        check_gpa(gpa)?;
+
+        let mut inner = self.inner.lock();
+        let pte = inner.leaf_pte(gpa, true)?.unwrap();
+        let flags = PageTableFlags::VALID
+            | PageTableFlags::USER
+            | PageTableFlags::ACCESSED
+            | PageTableFlags::DIRTY
+            | PageTableFlags::READABLE
+            | PageTableFlags::WRITABLE;
+        // SAFETY: The PTE is in a table owned by `inner`.
+        unsafe { pte.write(make_pte(file.paddr(), flags)) };
+        inner.frames.remove(&gpa);
+        inner.interrupt_files.insert(gpa, file);
+        drop(inner);
+
+        self.bump_generation();
+        Ok(())
+    }
+
+    /// Returns the frame that is mapped to a guest physical page.
+    pub fn query(&self, gpa: usize) -> Option<UFrame> {
+        self.inner
+            .lock()
+            .frames
+            .get(&gpa.align_down(PAGE_SIZE))
+            .cloned()
+    }
+
+    /// Returns the value of `hgatp` that uses the table.
+    pub fn hgatp(&self) -> usize {
+        HGATP_MODE_SV39X4 | (self.inner.lock().root.start_paddr() / PAGE_SIZE)
+    }
+
+    /// Returns the generation of the table, which changes whenever the table is modified.
+    pub fn generation(&self) -> usize {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    fn bump_generation(&self) {
+        let generation = NEXT_GENERATION.fetch_add(1, Ordering::Relaxed);
+        self.generation.store(generation, Ordering::Release);
+    }
+}
+
+impl Inner {
+    /// Returns the pointer to the leaf PTE of a guest physical page.
+    ///
+    /// The missing tables are allocated if `create` is true, otherwise `None` is returned.
+    fn leaf_pte(&mut self, gpa: usize, create: bool) -> Result<Option<*mut u64>> {
+        let mut table = self.root.start_paddr();
+        for level in [2, 1] {
+            let pte = pte_ptr(table, pte_index(gpa, level));
+            // SAFETY: The PTE is in a table owned by `self`.
+            let value = unsafe { pte.read() };
+            if value & PageTableFlags::VALID.bits() as u64 != 0 {
+                table = pte_paddr(value);
+                continue;
+            }
+            if !create {
+                return Ok(None);
+            }
+
+            let frame = FrameAllocOptions::new().alloc_frame()?;
+            table = frame.start_paddr();
+            self.tables.push(frame);
+            // SAFETY: The PTE is in a table owned by `self`, and the new table is zeroed.
+            unsafe { pte.write(make_pte(table, PageTableFlags::VALID)) };
+        }
+        Ok(Some(pte_ptr(table, pte_index(gpa, 0))))
+    }
+}
+
+fn check_gpa(gpa: usize) -> Result<()> {
+    if gpa % PAGE_SIZE != 0 || gpa >> GPA_BITS != 0 {
+        return Err(Error::InvalidArgs);
+    }
+    Ok(())
+}
+
+fn pte_index(gpa: usize, level: usize) -> usize {
+    let index = gpa >> (12 + 9 * level);
+    if level == 2 {
+        index % NR_ROOT_ENTRIES
+    } else {
+        index % NR_ENTRIES
+    }
+}
+
+fn pte_ptr(table: Paddr, index: usize) -> *mut u64 {
+    (paddr_to_vaddr(table) as *mut u64).wrapping_add(index)
+}
+
+fn pte_paddr(pte: u64) -> Paddr {
+    ((pte >> 10) as usize & ((1 << 44) - 1)) * PAGE_SIZE
+}
+
+fn make_pte(paddr: Paddr, flags: PageTableFlags) -> u64 {
+    (((paddr / PAGE_SIZE) << 10) | flags.bits()) as u64
+}
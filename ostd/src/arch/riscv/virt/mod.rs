@@ -0,0 +1,72 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Virtualization, i.e., running guest VMs with the hypervisor (H) extension.
+//!
+//! With the H extension, the kernel runs in the HS mode and a guest kernel runs in the VS mode
+//! (and its user programs in the VU mode). A virtual CPU is represented by a [`GuestContext`],
+//! which holds the guest registers and the VS CSRs. Like [`UserContext`], running the context
+//! switches to the guest and returns when a trap that must be handled by the host occurs, which
+//! is decoded as a [`VmExit`].
+//!
+//! The guest physical addresses are translated by the G-stage page table (see
+//! [`GuestPageTable`]), which maps them to the host frames.
+//!
+//! The interrupts are injected into the guest by setting the virtual interrupts in `hvip` (see
+//! [`GuestContext::set_interrupt_pending`]), or by the guest interrupt files of AIA (see
+//! [`GuestInterruptFile`]) if the platform has them. The guest reads its time from the `time`
+//! CSR, which is the host time plus the time delta of the context.
+//!
+//! The traps that can be handled by the guest kernel itself (e.g., the system calls and the
+//! page faults of the guest user programs) are delegated to the VS mode, so they never cause VM
+//! exits.
+//!
+//! Reference: <https://github.com/riscv/riscv-isa-manual/blob/main/src/hypervisor.adoc>
+//!
+//! [`UserContext`]: crate::user::UserContext
+
+/// Reads a CSR by its number, which is used for the CSRs that the assembler may not know.
+macro_rules! read_csr {
+    ($csr:expr) => {{
+        let value: usize;
+        // SAFETY: Reading a CSR has no side effects on the memory.
+        unsafe { core::arch::asm!("csrr {}, {csr}", out(reg) value, csr = const $csr) };
+        value
+    }};
+}
+
+/// Writes a CSR by its number, which is used for the CSRs that the assembler may not know.
+macro_rules! write_csr {
+    ($csr:expr, $value:expr) => {{
+        let value: usize = $value;
+        // SAFETY: The CSRs written by this module do not affect the memory safety of the host.
+        unsafe { core::arch::asm!("csrw {csr}, {}", in(reg) value, csr = const $csr) };
+    }};
+}
+
+mod aia;
+mod context;
+mod exit;
+mod gstage;
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+pub use aia::{nr_guest_files, GuestInterruptFile};
+pub use context::{GuestContext, VirtualInterrupt, VsCsrs};
+pub use exit::{GuestAccess, MmioInsn, VmExit};
+pub use gstage::{GuestPageTable, GuestPerms};
+
+use crate::cpu::{cpu_features, CpuFeatures};
+
+static IS_SUPPORTED: AtomicBool = AtomicBool::new(false);
+
+pub(super) fn init() {
+    if cpu_features().contains(CpuFeatures::HYPERVISOR) {
+        IS_SUPPORTED.store(true, Ordering::Relaxed);
+        aia::init();
+    }
+}
+
+/// Returns whether the guest VMs can be run, i.e., whether the H extension is available.
+pub fn is_supported() -> bool {
+    IS_SUPPORTED.load(Ordering::Relaxed)
+}
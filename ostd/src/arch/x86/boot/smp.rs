@@ -27,6 +27,8 @@
 //! This sequence does not need to be strictly followed, and there may be
 //! different considerations in different systems.
 
+use core::ops::Range;
+
 use acpi::madt::MadtEntry;
 
 use crate::{
@@ -128,6 +130,13 @@ pub(crate) unsafe fn bringup_all_aps(info_ptr: *mut PerApRawInfo, pt_ptr: Paddr,
     });
 }
 
+/// Returns the physical memory range of the boot stack of the BSP.
+///
+/// The stack is not freed, so nothing is returned.
+pub(crate) fn bsp_boot_stack() -> Option<Range<Paddr>> {
+    None
+}
+
 /// This is where the linker load the symbols in the `.ap_boot` section.
 /// The BSP would copy the AP boot code to this address.
 const AP_BOOT_START_PA: usize = 0x8000;
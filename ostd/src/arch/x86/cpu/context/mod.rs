@@ -23,7 +23,7 @@ use x86_64::registers::{
 };
 
 use crate::{
-    arch::x86::CPU_FEATURES,
+    cpu::{cpu_features, CpuFeatures},
     task::scheduler,
     trap::call_irq_callback_functions,
     user::{ReturnReason, UserContextApi, UserContextApiInternal},
@@ -463,7 +463,7 @@ struct XSaveArea {
 
 impl XSaveArea {
     fn init() -> Box<Self> {
-        let features = if CPU_FEATURES.get().unwrap().has_xsave() {
+        let features = if cpu_features().contains(CpuFeatures::XSAVE) {
             XCr0::read().bits() & XSTATE_MAX_FEATURES.get().unwrap()
         } else {
             0
@@ -486,7 +486,7 @@ impl FpuState {
     /// Initializes a new instance.
     pub fn init() -> Self {
         let mut area_size = size_of::<FxSaveArea>();
-        if CPU_FEATURES.get().unwrap().has_xsave() {
+        if cpu_features().contains(CpuFeatures::XSAVE) {
             area_size = area_size.max(*XSAVE_AREA_SIZE.get().unwrap());
         }
 
@@ -506,7 +506,7 @@ impl FpuState {
     pub fn save(&self) {
         let mem_addr = &*self.state_area as *const _ as *mut u8;
 
-        if CPU_FEATURES.get().unwrap().has_xsave() {
+        if cpu_features().contains(CpuFeatures::XSAVE) {
             unsafe { _xsave64(mem_addr, XFEATURE_MASK_USER_RESTORE) };
         } else {
             unsafe { _fxsave64(mem_addr) };
@@ -525,7 +525,7 @@ impl FpuState {
 
         let mem_addr = &*self.state_area as *const _ as *const u8;
 
-        if CPU_FEATURES.get().unwrap().has_xsave() {
+        if cpu_features().contains(CpuFeatures::XSAVE) {
             let rs_mask = XFEATURE_MASK_USER_RESTORE & XSTATE_MAX_FEATURES.get().unwrap();
 
             unsafe { _xrstor64(mem_addr, rs_mask) };
@@ -609,7 +609,7 @@ pub(in crate::arch::x86) fn enable_essential_features() {
         size
     });
 
-    if CPU_FEATURES.get().unwrap().has_fpu() {
+    if cpu_features().contains(CpuFeatures::FPU) {
         let mut cr0 = Cr0::read();
         cr0.remove(Cr0Flags::TASK_SWITCHED | Cr0Flags::EMULATE_COPROCESSOR);
 
@@ -12,9 +12,10 @@ use x86_64::registers::rflags::{self, RFlags};
 
 use super::iommu::{alloc_irt_entry, has_interrupt_remapping, IrtEntryHandle};
 use crate::{
-    cpu::CpuId,
+    cpu::{CpuId, CpuSet},
     sync::{LocalIrqDisabled, Mutex, PreemptDisabled, RwLock, RwLockReadGuard, SpinLock},
     trap::TrapFrame,
+    Error,
 };
 
 /// The global allocator for software defined IRQ lines.
@@ -125,6 +126,34 @@ impl IrqLine {
         self.callback_list.read()
     }
 
+    /// Masks the IRQ line.
+    ///
+    /// The IRQ lines are not bound to their interrupt sources (e.g., the pins of the I/O APIC
+    /// or the MSIs), so this does nothing. The interrupts that arrive during the masking are
+    /// still delivered, which is harmless for the MSIs since they are edge-triggered.
+    pub fn mask(&self) {}
+
+    /// Unmasks the IRQ line.
+    pub fn unmask(&self) {}
+
+    /// Returns the CPUs to which the interrupts are delivered.
+    ///
+    /// The I/O APIC entries and the MSIs always target the local APIC of the BSP.
+    pub fn affinity(&self) -> CpuSet {
+        CpuSet::from(CpuId::bsp())
+    }
+
+    /// Sets the CPUs to which the interrupts are delivered.
+    ///
+    /// The interrupts can only be delivered to the BSP, so the set must contain the BSP.
+    pub fn set_affinity(&self, cpus: &CpuSet) -> Result<(), Error> {
+        if cpus.contains(CpuId::bsp()) {
+            Ok(())
+        } else {
+            Err(Error::InvalidArgs)
+        }
+    }
+
     /// Registers a callback that will be invoked when the IRQ is active.
     ///
     /// A handle to the callback is returned. Dropping the handle
@@ -112,6 +112,12 @@ pub(crate) fn tlb_flush_all_including_global() {
     }
 }
 
+/// Synchronizes the instruction fetches of all CPUs with the modified code.
+pub(crate) fn flush_icache_all() {
+    // The instruction caches are coherent with the data caches on x86. The
+    // modified code needs no synchronization if it has not been executed.
+}
+
 #[derive(Clone, Copy, Pod, Default)]
 #[repr(C)]
 pub struct PageTableEntry(usize);
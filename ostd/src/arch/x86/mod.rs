@@ -11,7 +11,9 @@ pub mod iommu;
 pub(crate) mod irq;
 pub(crate) mod kernel;
 pub(crate) mod mm;
+pub(crate) mod module;
 pub(crate) mod pci;
+pub(crate) mod power;
 pub mod qemu;
 pub mod serial;
 pub mod task;
@@ -21,9 +23,12 @@ pub mod trap;
 use allocator::construct_io_mem_allocator_builder;
 use cfg_if::cfg_if;
 use spin::Once;
-use x86::cpuid::{CpuId, FeatureInfo};
+use x86::cpuid::CpuId;
 
-use crate::if_tdx_enabled;
+use crate::{
+    cpu::{cpu_features, features, CpuFeatures},
+    if_tdx_enabled,
+};
 
 cfg_if! {
     if #[cfg(feature = "cvm_guest")] {
@@ -62,7 +67,7 @@ pub(crate) fn init_cvm_guest() {
     }
 }
 
-static CPU_FEATURES: Once<FeatureInfo> = Once::new();
+static FEATURES_DETECTED: Once<()> = Once::new();
 
 /// Architecture-specific initialization on the bootstrapping processor.
 ///
@@ -144,6 +149,18 @@ pub fn read_tsc() -> u64 {
     unsafe { _rdtsc() }
 }
 
+/// Reads the clock of the scheduler, in the units of the TSC.
+///
+/// Unlike [`read_tsc`], the clock is monotonic even if the caller migrates across the
+/// processors, whose TSCs may not be perfectly synchronized.
+pub fn sched_clock() -> u64 {
+    static LAST: core::sync::atomic::AtomicU64 = core::sync::atomic::AtomicU64::new(0);
+
+    let now = read_tsc();
+    let last = LAST.fetch_max(now, core::sync::atomic::Ordering::Relaxed);
+    now.max(last)
+}
+
 /// Reads a hardware generated 64-bit random value.
 ///
 /// Returns None if no random value was generated.
@@ -163,27 +180,35 @@ pub fn read_random() -> Option<u64> {
     None
 }
 
-fn has_avx512() -> bool {
-    use core::arch::x86_64::{__cpuid, __cpuid_count};
-
-    let cpuid_result = unsafe { __cpuid(0) };
-    if cpuid_result.eax < 7 {
-        // CPUID function 7 is not supported
-        return false;
+/// Detects the features of the CPU by `CPUID`.
+fn detect_cpu_features() -> CpuFeatures {
+    let cpuid = CpuId::new();
+    let mut features = CpuFeatures::empty();
+
+    let feature_info = cpuid.get_feature_info().unwrap();
+    features.set(CpuFeatures::FPU, feature_info.has_fpu());
+    features.set(CpuFeatures::XSAVE, feature_info.has_xsave());
+    features.set(CpuFeatures::VECTOR, feature_info.has_avx());
+    features.set(CpuFeatures::HYPERVISOR, feature_info.has_vmx());
+    features.set(CpuFeatures::CACHE_BLOCK_MGMT, feature_info.has_clflush());
+    features.set(
+        CpuFeatures::AES_ENC | CpuFeatures::AES_DEC,
+        feature_info.has_aesni(),
+    );
+
+    // CPUID function 7 may not be supported, in which case there are no extended features.
+    if let Some(extended_info) = cpuid.get_extended_feature_info() {
+        features.set(CpuFeatures::VECTOR_512, extended_info.has_avx512f());
+        features.set(CpuFeatures::SHA2, extended_info.has_sha());
     }
 
-    let cpuid_result = unsafe { __cpuid_count(7, 0) };
-    // Check for AVX-512 Foundation (bit 16 of ebx)
-    cpuid_result.ebx & (1 << 16) != 0
+    features
 }
 
 pub(crate) fn enable_cpu_features() {
     use x86_64::registers::{control::Cr4Flags, model_specific::EferFlags, xcontrol::XCr0Flags};
 
-    CPU_FEATURES.call_once(|| {
-        let cpuid = CpuId::new();
-        cpuid.get_feature_info().unwrap()
-    });
+    FEATURES_DETECTED.call_once(|| features::init(detect_cpu_features()));
 
     cpu::context::enable_essential_features();
 
@@ -200,7 +225,7 @@ pub(crate) fn enable_cpu_features() {
     let mut xcr0 = x86_64::registers::xcontrol::XCr0::read();
     xcr0 |= XCr0Flags::AVX | XCr0Flags::SSE;
 
-    if has_avx512() {
+    if cpu_features().contains(CpuFeatures::VECTOR_512) {
         xcr0 |= XCr0Flags::OPMASK | XCr0Flags::ZMM_HI256 | XCr0Flags::HI16_ZMM;
     }
 
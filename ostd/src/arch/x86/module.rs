@@ -0,0 +1,87 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The relocations of the kernel extensions on x86-64.
+//!
+//! The extensions should be built with the kernel code model (`-C
+//! code-model=kernel`), since they are loaded within 2 GiB from the kernel.
+
+use crate::module::{ModuleError, Relocation};
+
+/// The machine of the ELF objects, which is `EM_X86_64`.
+pub(crate) const ELF_MACHINE: u16 = 62;
+
+const R_X86_64_NONE: u32 = 0;
+const R_X86_64_64: u32 = 1;
+const R_X86_64_PC32: u32 = 2;
+const R_X86_64_PLT32: u32 = 4;
+const R_X86_64_GOTPCREL: u32 = 9;
+const R_X86_64_32: u32 = 10;
+const R_X86_64_32S: u32 = 11;
+const R_X86_64_PC64: u32 = 24;
+const R_X86_64_GOTPCRELX: u32 = 41;
+const R_X86_64_REX_GOTPCRELX: u32 = 42;
+
+/// Returns whether the relocation refers to the GOT entry of its symbol.
+pub(crate) fn needs_got_entry(typ: u32) -> bool {
+    matches!(
+        typ,
+        R_X86_64_GOTPCREL | R_X86_64_GOTPCRELX | R_X86_64_REX_GOTPCRELX
+    )
+}
+
+/// Applies the relocations of a section.
+///
+/// # Safety
+///
+/// The places of the relocations must be valid for writes before the ends
+/// of their sections.
+pub(crate) unsafe fn apply_relocations(relocs: &[Relocation]) -> Result<(), ModuleError> {
+    for reloc in relocs {
+        let typ = reloc.typ;
+        let overflow = || ModuleError::RelocationOverflow(typ);
+        let (value, width) = match typ {
+            R_X86_64_NONE => continue,
+            R_X86_64_64 => (reloc.value(), 8),
+            R_X86_64_PC64 => (reloc.pcrel() as u64, 8),
+            R_X86_64_32 => (
+                u32::try_from(reloc.value()).map_err(|_| overflow())? as u64,
+                4,
+            ),
+            R_X86_64_32S => (
+                i32::try_from(reloc.value() as i64).map_err(|_| overflow())? as u64,
+                4,
+            ),
+            R_X86_64_PC32 | R_X86_64_PLT32 => (
+                i32::try_from(reloc.pcrel()).map_err(|_| overflow())? as u64,
+                4,
+            ),
+            R_X86_64_GOTPCREL | R_X86_64_GOTPCRELX | R_X86_64_REX_GOTPCRELX => {
+                let got_entry = reloc.got_entry.unwrap() as i64;
+                let pcrel = got_entry
+                    .wrapping_add(reloc.addend)
+                    .wrapping_sub(reloc.place as i64);
+                (i32::try_from(pcrel).map_err(|_| overflow())? as u64, 4)
+            }
+            _ => return Err(ModuleError::UnsupportedRelocation(typ)),
+        };
+
+        if reloc
+            .place
+            .checked_add(width)
+            .is_none_or(|end| end > reloc.section_end)
+        {
+            return Err(ModuleError::InvalidImage("relocation out of the section"));
+        }
+        let ptr = reloc.place as *mut u8;
+        // SAFETY: The place is valid for writes of `width` bytes, as checked
+        // above and guaranteed by the caller.
+        unsafe {
+            if width == 8 {
+                ptr.cast::<u64>().write_unaligned(value);
+            } else {
+                ptr.cast::<u32>().write_unaligned(value as u32);
+            }
+        }
+    }
+    Ok(())
+}
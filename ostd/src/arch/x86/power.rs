@@ -0,0 +1,19 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Suspend-to-RAM, which is not supported on x86 yet.
+
+use crate::{prelude::*, Error};
+
+/// Returns whether the platform supports suspend-to-RAM.
+pub(crate) fn is_supported() -> bool {
+    false
+}
+
+/// Suspends the system to RAM.
+///
+/// # Safety
+///
+/// The local IRQs must be disabled, and the other CPUs must have been stopped.
+pub(crate) unsafe fn suspend() -> Result<()> {
+    Err(Error::InvalidArgs)
+}
@@ -30,6 +30,7 @@ use crate::{
     arch::irq::{disable_local, enable_local},
     cpu::context::{CpuException, CpuExceptionInfo, PageFaultErrorCode},
     cpu_local_cell, if_tdx_enabled,
+    kallsyms::SymbolizedAddr,
     mm::{
         kspace::{KERNEL_PAGE_TABLE, LINEAR_MAPPING_BASE_VADDR, LINEAR_MAPPING_VADDR_RANGE},
         page_prop::{CachePolicy, PageProperty},
@@ -265,8 +266,10 @@ extern "sysv64" fn trap_handler(f: &mut TrapFrame) {
         Some(exception) => {
             enable_local_if(was_irq_enabled);
             panic!(
-                "cannot handle kernel CPU exception: {:?}, trapframe: {:?}",
-                exception, f
+                "cannot handle kernel CPU exception: {:?} at {}, trapframe: {:?}",
+                exception,
+                SymbolizedAddr(f.rip),
+                f
             );
         }
         None => {
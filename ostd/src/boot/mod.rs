@@ -7,6 +7,7 @@
 //!  3. the routine booting the other processors in the SMP context.
 
 pub mod memory_region;
+pub mod param;
 pub mod smp;
 
 use alloc::{
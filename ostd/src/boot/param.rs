@@ -0,0 +1,258 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Boot-time parameters of the subsystems.
+//!
+//! Like the module parameters of the built-in modules in Linux, a subsystem can declare its
+//! tunables as the statics of [`BootParam`] instead of the compile-time constants. Each parameter
+//! is named as `module.name` and has a default value. It takes the value of the last
+//! `module.name=value` argument in the kernel command line when it is registered with
+//! [`register_param`], and keeps the default value if the argument is absent or invalid.
+//!
+//! The parameters in OSTD are registered during the initialization of their subsystems. The
+//! parameters out of OSTD should be registered before their values are used, e.g., in the
+//! initialization functions of their subsystems. The registered parameters can be found by
+//! [`params`] (e.g., to dump them in a procfs-like interface).
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use spin::Once;
+
+use super::EARLY_INFO;
+use crate::sync::SpinLock;
+
+/// The type of the value of a boot-time parameter.
+pub trait ParamValue: Copy + Send + Sync + fmt::Display + 'static {
+    /// Parses the value in the kernel command line.
+    ///
+    /// The value is `None` if the parameter is given without `=`.
+    fn parse(value: Option<&'static str>) -> Option<Self>;
+}
+
+macro_rules! impl_param_value_for_int {
+    ($($ty:ty),*) => {
+        $(
+            impl ParamValue for $ty {
+                fn parse(value: Option<&'static str>) -> Option<Self> {
+                    let value = value?;
+                    match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+                        Some(hex) => <$ty>::from_str_radix(hex, 16).ok(),
+                        None => value.parse().ok(),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_param_value_for_int!(u8, u16, u32, u64, usize, i32, i64);
+
+impl ParamValue for bool {
+    /// A boolean is set to `true` if the parameter is given without a value.
+    fn parse(value: Option<&'static str>) -> Option<Self> {
+        match value {
+            None | Some("1" | "y" | "Y" | "on") => Some(true),
+            Some("0" | "n" | "N" | "off") => Some(false),
+            Some(_) => None,
+        }
+    }
+}
+
+impl ParamValue for &'static str {
+    fn parse(value: Option<&'static str>) -> Option<Self> {
+        Some(value.unwrap_or_default())
+    }
+}
+
+/// A boot-time parameter.
+///
+/// # Examples
+///
+/// ```
+/// use ostd::boot::param::{register_param, BootParam};
+///
+/// static MAX_DEPTH: BootParam<u32> = BootParam::new("my_system", "max_depth", 8);
+///
+/// register_param(&MAX_DEPTH);
+/// let max_depth = MAX_DEPTH.get();
+/// ```
+pub struct BootParam<T: ParamValue> {
+    module: &'static str,
+    name: &'static str,
+    default: T,
+    value: Once<T>,
+}
+
+impl<T: ParamValue> BootParam<T> {
+    /// Creates a parameter with the default value.
+    ///
+    /// The parameter is identified by the name of its module (i.e., the subsystem that the
+    /// parameter belongs to, such as "plic" or "sched") and its own name.
+    pub const fn new(module: &'static str, name: &'static str, default: T) -> Self {
+        Self {
+            module,
+            name,
+            default,
+            value: Once::new(),
+        }
+    }
+
+    /// Returns the value of the parameter.
+    ///
+    /// The value is the default one until the parameter is registered.
+    pub fn get(&self) -> T {
+        *self.value.get().unwrap_or(&self.default)
+    }
+
+    /// Returns the default value of the parameter.
+    pub fn default_value(&self) -> T {
+        self.default
+    }
+
+    /// Returns the valid values of all the arguments of the parameter in the kernel command line.
+    ///
+    /// This is for the parameters that can be given multiple times, e.g., one for each device. The
+    /// values are in the order of the arguments.
+    pub fn values(&self) -> impl Iterator<Item = T> + '_ {
+        find_args(kernel_cmdline(), self.module, self.name).filter_map(T::parse)
+    }
+
+    fn parse_cmdline(&self) {
+        let Some(value) = find_args(kernel_cmdline(), self.module, self.name).last() else {
+            return;
+        };
+        match T::parse(value) {
+            Some(value) => {
+                self.value.call_once(|| value);
+            }
+            None => log::warn!(
+                "Invalid value {:?} of the boot-time parameter {}.{}",
+                value.unwrap_or_default(),
+                self.module,
+                self.name
+            ),
+        }
+    }
+}
+
+/// A registered boot-time parameter, whose value can be formatted regardless of its type.
+pub trait Param: Sync {
+    /// Returns the name of the module that the parameter belongs to.
+    fn module(&self) -> &'static str;
+
+    /// Returns the name of the parameter.
+    fn name(&self) -> &'static str;
+
+    /// Returns whether the parameter is given a valid value in the kernel command line.
+    fn is_set(&self) -> bool;
+
+    /// Formats the value of the parameter.
+    fn fmt_value(&self, f: &mut dyn fmt::Write) -> fmt::Result;
+}
+
+impl<T: ParamValue> Param for BootParam<T> {
+    fn module(&self) -> &'static str {
+        self.module
+    }
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn is_set(&self) -> bool {
+        self.value.is_completed()
+    }
+
+    fn fmt_value(&self, f: &mut dyn fmt::Write) -> fmt::Result {
+        write!(f, "{}", self.get())
+    }
+}
+
+static PARAMS: SpinLock<Vec<&'static dyn Param>> = SpinLock::new(Vec::new());
+
+/// Registers a parameter, which takes its value from the kernel command line.
+///
+/// Registering a parameter more than once has no effect.
+pub fn register_param<T: ParamValue>(param: &'static BootParam<T>) {
+    let mut params = PARAMS.lock();
+    if params
+        .iter()
+        .any(|registered| core::ptr::addr_eq(*registered, param))
+    {
+        return;
+    }
+    param.parse_cmdline();
+    params.push(param);
+}
+
+/// Returns all the registered parameters.
+pub fn params() -> Vec<&'static dyn Param> {
+    PARAMS.lock().clone()
+}
+
+fn kernel_cmdline() -> &'static str {
+    EARLY_INFO.get().unwrap().kernel_cmdline
+}
+
+/// Finds the values of the `module.name[=value]` arguments in the kernel command line.
+///
+/// As in Linux, the dashes and the underscores in the names are interchangeable.
+fn find_args<'a>(
+    cmdline: &'a str,
+    module: &'a str,
+    name: &'a str,
+) -> impl Iterator<Item = Option<&'a str>> + 'a {
+    fn name_eq(lhs: &str, rhs: &str) -> bool {
+        let normalize = |c: char| if c == '-' { '_' } else { c };
+        lhs.len() == rhs.len() && lhs.chars().map(normalize).eq(rhs.chars().map(normalize))
+    }
+
+    cmdline
+        .split_whitespace()
+        // The arguments after `--` are passed to the init process.
+        .take_while(|arg| *arg != "--")
+        .filter_map(move |arg| {
+            let (key, value) = match arg.split_once('=') {
+                Some((key, value)) => (key, Some(value)),
+                None => (arg, None),
+            };
+            let (arg_module, arg_name) = key.split_once('.')?;
+            (name_eq(arg_module, module) && name_eq(arg_name, name)).then_some(value)
+        })
+}
+
+#[cfg(ktest)]
+mod test {
+    use super::*;
+    use crate::prelude::ktest;
+
+    #[ktest]
+    fn find_args_in_cmdline() {
+        let cmdline = "console=hvc0 sched.latency_ns=4000000 plic.max-priority=7 \
+            sched.latency_ns=0x1000 sched.quiet -- sched.latency_ns=1";
+
+        let values: Vec<_> = find_args(cmdline, "sched", "latency_ns").collect();
+        assert_eq!(values, [Some("4000000"), Some("0x1000")]);
+        let values: Vec<_> = find_args(cmdline, "plic", "max_priority").collect();
+        assert_eq!(values, [Some("7")]);
+        let values: Vec<_> = find_args(cmdline, "sched", "quiet").collect();
+        assert_eq!(values, [None]);
+        assert_eq!(find_args(cmdline, "console", "").count(), 0);
+    }
+
+    #[ktest]
+    fn parse_values() {
+        assert_eq!(u64::parse(Some("4000000")), Some(4_000_000));
+        assert_eq!(u32::parse(Some("0x1f")), Some(0x1f));
+        assert_eq!(i32::parse(Some("-3")), Some(-3));
+        assert_eq!(u8::parse(Some("256")), None);
+        assert_eq!(u64::parse(None), None);
+        assert_eq!(bool::parse(None), Some(true));
+        assert_eq!(bool::parse(Some("off")), Some(false));
+        assert_eq!(bool::parse(Some("maybe")), None);
+        assert_eq!(
+            <&str>::parse(Some("512@0x10001000:1")),
+            Some("512@0x10001000:1")
+        );
+    }
+}
@@ -8,12 +8,13 @@ use core::sync::atomic::{AtomicBool, Ordering};
 use spin::Once;
 
 use crate::{
-    arch::boot::smp::bringup_all_aps,
-    cpu,
+    arch::boot::smp::{bringup_all_aps, bsp_boot_stack},
+    cpu::{self, CpuId},
     mm::{
         frame::{meta::KernelMeta, Segment},
         paddr_to_vaddr, FrameAllocOptions, PAGE_SIZE,
     },
+    sync::SpinLock,
     task::Task,
 };
 
@@ -30,12 +31,9 @@ struct ApBootInfo {
 
 struct PerApInfo {
     is_started: AtomicBool,
-    // TODO: When the AP starts up and begins executing tasks, the boot stack will
-    // no longer be used, and the `Segment` can be deallocated (this problem also
-    // exists in the boot processor, but the memory it occupies should be returned
-    // to the frame allocator).
-    #[expect(dead_code)]
-    boot_stack_pages: Segment<KernelMeta>,
+    /// The boot stack, which is deallocated after the AP begins executing tasks
+    /// (see [`free_boot_stack`]).
+    boot_stack_pages: SpinLock<Option<Segment<KernelMeta>>>,
 }
 
 /// Raw boot information for APs.
@@ -107,7 +105,7 @@ pub(crate) unsafe fn boot_all_aps() {
 
         per_ap_info.push(PerApInfo {
             is_started: AtomicBool::new(false),
-            boot_stack_pages,
+            boot_stack_pages: SpinLock::new(Some(boot_stack_pages)),
         });
     }
 
@@ -181,6 +179,36 @@ fn ap_early_entry(cpu_id: u32) -> ! {
     unreachable!("`yield_now` in the boot context should not return");
 }
 
+/// Frees the boot stack of the current processor.
+///
+/// The boot context is never resumed after the processor switches to its first
+/// task, so its stack is no longer used and is returned to the frame allocator.
+///
+/// # Safety
+///
+/// This function must be called only once on each processor, after it has
+/// switched from the boot context to a task.
+pub(crate) unsafe fn free_boot_stack() {
+    let cpu = cpu::current_cpu_racy();
+    if cpu == CpuId::bsp() {
+        let Some(range) = bsp_boot_stack() else {
+            return;
+        };
+        // SAFETY: The stack is in the kernel image, whose frames are forgotten
+        // handles with `KernelMeta`. It is no longer used, as guaranteed by
+        // the caller.
+        drop(unsafe { Segment::<KernelMeta>::from_raw(range) });
+        return;
+    }
+
+    let ap_boot_info = AP_BOOT_INFO.get().unwrap();
+    let boot_stack_pages = ap_boot_info.per_ap_info[cpu.as_usize() - 1]
+        .boot_stack_pages
+        .lock()
+        .take();
+    drop(boot_stack_pages);
+}
+
 fn wait_for_all_aps_started() {
     fn is_all_aps_started() -> bool {
         let ap_boot_info = AP_BOOT_INFO.get().unwrap();
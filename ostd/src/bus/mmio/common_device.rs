@@ -42,6 +42,11 @@ impl MmioCommonDevice {
                 CachePolicy::Uncacheable,
             )
         };
+        Self::with_io_mem(io_mem, handle)
+    }
+
+    /// Creates a device with the I/O memory whose magic value has been checked.
+    pub(super) fn with_io_mem(io_mem: IoMem, handle: IrqLine) -> Self {
         let res = Self {
             io_mem,
             irq: handle,
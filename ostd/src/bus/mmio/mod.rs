@@ -14,6 +14,12 @@ use cfg_if::cfg_if;
 use log::debug;
 
 use self::bus::MmioBus;
+#[cfg(target_arch = "riscv64")]
+use crate::{
+    boot::param::{register_param, BootParam, ParamValue},
+    io::IoMem,
+    mm::{Paddr, VmIoOnce},
+};
 use crate::{
     bus::mmio::common_device::MmioCommonDevice, if_tdx_enabled, mm::paddr_to_vaddr, sync::SpinLock,
     trap::IrqLine,
@@ -46,6 +52,77 @@ pub(crate) fn init() {
     // FIXME: The address 0xFEB0_0000 is obtained from an instance of microvm, and it may not work in other architecture.
     #[cfg(target_arch = "x86_64")]
     iter_range(0xFEB0_0000..0xFEB0_4000);
+    #[cfg(target_arch = "riscv64")]
+    probe_cmdline_devices();
+}
+
+/// The virtio-mmio devices given in the kernel command line.
+///
+/// As in Linux, each device is given as `virtio_mmio.device=<size>@<base>:<irq>[:<id>]`, e.g.,
+/// `virtio_mmio.device=4K@0x10001000:1`, and the argument can be given multiple times. The IRQ
+/// is the interrupt source of the PLIC. The platform device ID is ignored.
+#[cfg(target_arch = "riscv64")]
+static CMDLINE_DEVICES: BootParam<&'static str> = BootParam::new("virtio_mmio", "device", "");
+
+#[cfg(target_arch = "riscv64")]
+fn probe_cmdline_devices() {
+    register_param(&CMDLINE_DEVICES);
+
+    let mut lock = MMIO_BUS.lock();
+    for arg in CMDLINE_DEVICES.values() {
+        let Some((range, irq_num)) = parse_device_arg(arg) else {
+            log::warn!("[Virtio]: Invalid MMIO device {:?}", arg);
+            continue;
+        };
+        debug!("[Virtio]: Probe MMIO range:{:x?}, irq:{}", range, irq_num);
+        let Ok(io_mem) = IoMem::acquire(range.clone()) else {
+            log::warn!("[Virtio]: MMIO range {:x?} is not available", range);
+            continue;
+        };
+        if !io_mem
+            .read_once::<u32>(0)
+            .is_ok_and(|magic| magic == VIRTIO_MMIO_MAGIC)
+        {
+            log::warn!("[Virtio]: No MMIO device at {:#x}", range.start);
+            continue;
+        }
+        // The device ID zero means a placeholder without a device.
+        if io_mem
+            .read_once::<u32>(8)
+            .is_ok_and(|device_id| device_id == 0)
+        {
+            continue;
+        }
+        let Ok(handle) = IrqLine::alloc_specific(irq_num) else {
+            log::warn!("[Virtio]: IRQ {} of the MMIO device is in use", irq_num);
+            continue;
+        };
+        lock.register_mmio_device(MmioCommonDevice::with_io_mem(io_mem, handle));
+    }
+}
+
+/// Parses `<size>@<base>:<irq>[:<id>]`, where the size can have a `K`, `M` or `G` suffix.
+#[cfg(target_arch = "riscv64")]
+fn parse_device_arg(arg: &'static str) -> Option<(Range<Paddr>, u8)> {
+    let (size, rest) = arg.split_once('@')?;
+    let (base, rest) = rest.split_once(':')?;
+    let irq = rest.split(':').next()?;
+
+    let (size, shift) = match size.as_bytes().last()? {
+        b'K' | b'k' => (&size[..size.len() - 1], 10),
+        b'M' | b'm' => (&size[..size.len() - 1], 20),
+        b'G' | b'g' => (&size[..size.len() - 1], 30),
+        _ => (size, 0),
+    };
+    let size = usize::parse(Some(size))?.checked_shl(shift)?;
+    let base = usize::parse(Some(base))?;
+    let irq = u8::parse(Some(irq))?;
+    // The registers before the configuration space take 0x100 bytes.
+    if size < 0x100 {
+        return None;
+    }
+
+    Some((base..base.checked_add(size)?, irq))
 }
 
 #[cfg(target_arch = "x86_64")]
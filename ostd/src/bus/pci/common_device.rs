@@ -12,6 +12,9 @@ use super::{
     device_info::{PciDeviceId, PciDeviceLocation},
 };
 
+/// The offset of the device-specific part of the configuration space.
+const DEVICE_SPECIFIC_CFG_OFFSET: u16 = 0x40;
+
 /// PCI common device, Contains a range of information and functions common to PCI devices.
 #[derive(Debug)]
 pub struct PciCommonDevice {
@@ -64,6 +67,26 @@ impl PciCommonDevice {
         )
     }
 
+    /// Reads a byte in the device-specific part of the configuration space.
+    ///
+    /// The device-specific part starts after the 64-byte common header.
+    pub fn read_config_u8(&self, offset: u16) -> u8 {
+        debug_assert!(offset >= DEVICE_SPECIFIC_CFG_OFFSET);
+        self.location.read8(offset)
+    }
+
+    /// Writes a byte in the device-specific part of the configuration space.
+    pub fn write_config_u8(&self, offset: u16, value: u8) {
+        debug_assert!(offset >= DEVICE_SPECIFIC_CFG_OFFSET);
+        self.location.write8(offset, value)
+    }
+
+    /// Writes a 16-bit value in the device-specific part of the configuration space.
+    pub fn write_config_u16(&self, offset: u16, value: u16) {
+        debug_assert!(offset >= DEVICE_SPECIFIC_CFG_OFFSET);
+        self.location.write16(offset, value)
+    }
+
     pub(super) fn new(location: PciDeviceLocation) -> Option<Self> {
         if location.read16(0) == 0xFFFF {
             // not exists
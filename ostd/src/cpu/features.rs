@@ -0,0 +1,71 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The features of the CPUs.
+//!
+//! The features are detected once during the boot by the architecture-specific code (e.g., by
+//! `CPUID` on x86, or by the ISA string in the device tree and the SBI probes on RISC-V), and
+//! are queried by [`cpu_features`] afterwards. All the CPUs are assumed to have the same
+//! features as the bootstrap processor.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use bitflags::bitflags;
+
+bitflags! {
+    /// The features of the CPUs that are relevant to the kernel.
+    ///
+    /// Some features are only available on specific architectures, and they are never set on
+    /// the others.
+    pub struct CpuFeatures: u64 {
+        /// The floating-point unit (the FPU on x86, or the F and D extensions on RISC-V).
+        const FPU              = 1 << 0;
+        /// The `XSAVE` family of instructions (x86 only).
+        const XSAVE            = 1 << 1;
+        /// The vector instructions (AVX on x86, or the V extension on RISC-V).
+        const VECTOR           = 1 << 2;
+        /// The 512-bit vector instructions (AVX-512 Foundation, x86 only).
+        const VECTOR_512       = 1 << 3;
+        /// The hardware virtualization (VMX on x86, or the H extension on RISC-V).
+        const HYPERVISOR       = 1 << 4;
+        /// The cache-block management instructions (`CLFLUSH` on x86, or the Zicbom extension
+        /// on RISC-V).
+        const CACHE_BLOCK_MGMT = 1 << 5;
+        /// The AES encryption instructions (AES-NI on x86, or the Zkne extension on RISC-V).
+        const AES_ENC          = 1 << 6;
+        /// The AES decryption instructions (AES-NI on x86, or the Zknd extension on RISC-V).
+        const AES_DEC          = 1 << 7;
+        /// The SHA-2 instructions (the SHA extensions on x86, or the Zknh extension on
+        /// RISC-V).
+        const SHA2             = 1 << 8;
+        /// The memory types in the page table entries (the Svpbmt extension, RISC-V only).
+        const PAGE_MEM_TYPES   = 1 << 9;
+        /// The SBI timer extension (RISC-V only).
+        const SBI_TIMER        = 1 << 16;
+        /// The SBI IPI extension (RISC-V only).
+        const SBI_IPI          = 1 << 17;
+        /// The SBI RFENCE extension (RISC-V only).
+        const SBI_RFENCE       = 1 << 18;
+        /// The SBI hart state management extension (RISC-V only).
+        const SBI_HSM          = 1 << 19;
+        /// The SBI performance monitoring unit extension (RISC-V only).
+        const SBI_PMU          = 1 << 20;
+        /// The SBI system suspend extension (RISC-V only).
+        const SBI_SUSP         = 1 << 21;
+    }
+}
+
+static FEATURES: AtomicU64 = AtomicU64::new(0);
+
+/// Returns the features of the CPUs.
+///
+/// The features are empty if they have not been detected yet.
+pub fn cpu_features() -> CpuFeatures {
+    CpuFeatures::from_bits_truncate(FEATURES.load(Ordering::Relaxed))
+}
+
+/// Records the detected features of the CPUs.
+pub(crate) fn init(features: CpuFeatures) {
+    FEATURES.store(features.bits(), Ordering::Relaxed);
+
+    log::info!("CPU features: {:?}", features);
+}
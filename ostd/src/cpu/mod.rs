@@ -2,9 +2,11 @@
 
 //! CPU-related definitions.
 
+pub mod features;
 pub mod local;
 pub mod set;
 
+pub use features::{cpu_features, CpuFeatures};
 pub use set::{AtomicCpuSet, CpuSet};
 
 cfg_if::cfg_if! {
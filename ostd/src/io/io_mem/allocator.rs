@@ -39,7 +39,6 @@ impl IoMemAllocator {
     /// # Safety
     ///
     /// The caller must have ownership of the MMIO region through the `IoMemAllocator::get` interface.
-    #[expect(dead_code)]
     pub(in crate::io) unsafe fn recycle(&self, range: Range<usize>) {
         let allocator = find_allocator(&self.allocators, &range).unwrap();
 
@@ -8,8 +8,8 @@ use core::ops::{Deref, Range};
 
 use align_ext::AlignExt;
 
-pub(super) use self::allocator::init;
 pub(crate) use self::allocator::IoMemAllocatorBuilder;
+pub(super) use self::allocator::{init, IO_MEM_ALLOCATOR};
 use crate::{
     if_tdx_enabled,
     mm::{
@@ -58,6 +58,11 @@ impl IoMem {
         self.limit
     }
 
+    /// Returns whether there are no other `IoMem` instances sharing the mapping.
+    pub(in crate::io) fn is_unique(&self) -> bool {
+        Arc::strong_count(&self.kvirt_area) == 1
+    }
+
     /// Slices the `IoMem`, returning another `IoMem` representing the subslice.
     ///
     /// # Panics
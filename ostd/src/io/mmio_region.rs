@@ -0,0 +1,248 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The MMIO regions claimed by the device drivers.
+//!
+//! A driver claims the physical MMIO range of its device (e.g., from the `reg` property in the
+//! device tree, or from a PCI BAR) as an [`MmioRegion`], which is recorded in a registry with
+//! the name of its owner. A range can only be claimed by one owner at a time, so two drivers
+//! that bind to the same device are detected when the latter claims the range, instead of both
+//! mapping and programming the device silently.
+//!
+//! The regions are mapped as uncacheable I/O memory, which uses the I/O memory type of Svpbmt
+//! on RISC-V if the extension is available.
+
+use alloc::{
+    collections::btree_map::BTreeMap,
+    string::{String, ToString},
+};
+use core::{marker::PhantomData, ops::Range};
+
+use super::{io_mem::IO_MEM_ALLOCATOR, IoMem};
+use crate::{
+    bus::pci::cfg_space::MemoryBar,
+    mm::{HasPaddr, Paddr, PodOnce, VmIo, VmIoOnce, VmReader, VmWriter},
+    prelude::*,
+    sync::SpinLock,
+    Error,
+};
+
+/// The owners of the claimed ranges, indexed by the start addresses.
+static CLAIMED_REGIONS: SpinLock<BTreeMap<Paddr, (Paddr, String)>> = SpinLock::new(BTreeMap::new());
+
+/// An MMIO region claimed by a device driver.
+///
+/// The region is released when it is dropped. If the [`IoMem`] of the region has been cloned
+/// by [`MmioRegion::io_mem`] and the clones are still alive, the range is kept unavailable to
+/// the other drivers, so that the device is never accessed by two owners.
+#[derive(Debug)]
+pub struct MmioRegion {
+    io_mem: IoMem,
+    owner: String,
+    /// Whether the range is acquired from the I/O memory allocator, to which it is recycled.
+    is_from_allocator: bool,
+}
+
+impl MmioRegion {
+    /// Claims the physical MMIO range for the owner.
+    ///
+    /// # Errors
+    ///
+    /// This method returns [`Error::AccessDenied`] if the range overlaps with a range that has
+    /// been claimed, or if the range is not available for the device drivers (e.g., the range
+    /// is in the physical memory or used by OSTD).
+    pub fn claim(range: Range<Paddr>, owner: &str) -> Result<Self> {
+        if range.is_empty() {
+            return Err(Error::InvalidArgs);
+        }
+        register(&range, owner)?;
+
+        let Some(io_mem) = IO_MEM_ALLOCATOR.get().unwrap().acquire(range.clone()) else {
+            unregister(&range);
+            return Err(Error::AccessDenied);
+        };
+        Ok(Self {
+            io_mem,
+            owner: owner.to_string(),
+            is_from_allocator: true,
+        })
+    }
+
+    /// Claims the MMIO range of the memory BAR of a PCI device for the owner.
+    ///
+    /// # Errors
+    ///
+    /// This method returns [`Error::AccessDenied`] if the BAR has been claimed.
+    pub fn claim_pci_bar(bar: &MemoryBar, owner: &str) -> Result<Self> {
+        let io_mem = bar.io_mem().clone();
+        let range = io_mem.paddr()..io_mem.paddr() + io_mem.length();
+        register(&range, owner)?;
+        Ok(Self {
+            io_mem,
+            owner: owner.to_string(),
+            is_from_allocator: false,
+        })
+    }
+
+    /// Claims the first range of the `reg` property of the device tree node, with the name of
+    /// the node as the owner.
+    ///
+    /// # Errors
+    ///
+    /// This method returns [`Error::InvalidArgs`] if the node has no `reg` property with a size,
+    /// or the errors of [`MmioRegion::claim`].
+    #[cfg(target_arch = "riscv64")]
+    pub fn claim_fdt_reg(node: &fdt::node::FdtNode) -> Result<Self> {
+        let region = node
+            .reg()
+            .and_then(|mut reg| reg.next())
+            .ok_or(Error::InvalidArgs)?;
+        let start = region.starting_address as Paddr;
+        let size = region.size.ok_or(Error::InvalidArgs)?;
+        Self::claim(start..start + size, node.name)
+    }
+
+    /// Returns the name of the owner.
+    pub fn owner(&self) -> &str {
+        &self.owner
+    }
+
+    /// Returns the length of the region.
+    pub fn length(&self) -> usize {
+        self.io_mem.length()
+    }
+
+    /// Returns the [`IoMem`] of the region.
+    ///
+    /// This is useful to pass the region to the APIs that take `IoMem`.
+    pub fn io_mem(&self) -> &IoMem {
+        &self.io_mem
+    }
+
+    /// Returns the register of type `T` at the offset.
+    ///
+    /// # Errors
+    ///
+    /// This method returns [`Error::InvalidArgs`] if the register is out of the region or is
+    /// misaligned.
+    pub fn reg<T: PodOnce>(&self, offset: usize) -> Result<MmioReg<'_, T>> {
+        let is_in_bounds = offset
+            .checked_add(size_of::<T>())
+            .is_some_and(|end| end <= self.length());
+        if !is_in_bounds || (self.paddr() + offset) % align_of::<T>() != 0 {
+            return Err(Error::InvalidArgs);
+        }
+        Ok(MmioReg {
+            region: self,
+            offset,
+            phantom: PhantomData,
+        })
+    }
+}
+
+impl HasPaddr for MmioRegion {
+    fn paddr(&self) -> Paddr {
+        self.io_mem.paddr()
+    }
+}
+
+impl VmIo for MmioRegion {
+    fn read(&self, offset: usize, writer: &mut VmWriter) -> Result<()> {
+        self.io_mem.read(offset, writer)
+    }
+
+    fn write(&self, offset: usize, reader: &mut VmReader) -> Result<()> {
+        self.io_mem.write(offset, reader)
+    }
+}
+
+impl VmIoOnce for MmioRegion {
+    fn read_once<T: PodOnce>(&self, offset: usize) -> Result<T> {
+        self.io_mem.read_once(offset)
+    }
+
+    fn write_once<T: PodOnce>(&self, offset: usize, new_val: &T) -> Result<()> {
+        self.io_mem.write_once(offset, new_val)
+    }
+}
+
+impl Drop for MmioRegion {
+    fn drop(&mut self) {
+        let range = self.paddr()..self.paddr() + self.length();
+        if !self.io_mem.is_unique() {
+            log::warn!(
+                "The MMIO region {:#x?} of {} is dropped while its mappings are in use",
+                range,
+                self.owner
+            );
+            return;
+        }
+
+        if self.is_from_allocator {
+            // SAFETY: The range is acquired from the allocator by `MmioRegion::claim`, and
+            // there are no other mappings of the range.
+            unsafe { IO_MEM_ALLOCATOR.get().unwrap().recycle(range.clone()) };
+        }
+        unregister(&range);
+    }
+}
+
+/// A register in an [`MmioRegion`], which is accessed by volatile loads and stores.
+#[derive(Debug)]
+pub struct MmioReg<'a, T: PodOnce> {
+    region: &'a MmioRegion,
+    offset: usize,
+    phantom: PhantomData<T>,
+}
+
+impl<T: PodOnce> MmioReg<'_, T> {
+    /// Reads the register.
+    pub fn read(&self) -> T {
+        // The register has been checked to be in the region.
+        self.region.read_once(self.offset).unwrap()
+    }
+
+    /// Writes the register.
+    pub fn write(&self, value: T) {
+        self.region.write_once(self.offset, &value).unwrap()
+    }
+
+    /// Reads the register, and writes the value returned by the closure back.
+    pub fn update(&self, f: impl FnOnce(T) -> T) {
+        self.write(f(self.read()))
+    }
+}
+
+/// Returns the claimed MMIO ranges and their owners, in the order of the addresses.
+pub fn claimed_regions() -> Vec<(Range<Paddr>, String)> {
+    CLAIMED_REGIONS
+        .lock()
+        .iter()
+        .map(|(start, (end, owner))| (*start..*end, owner.clone()))
+        .collect()
+}
+
+fn register(range: &Range<Paddr>, owner: &str) -> Result<()> {
+    let mut regions = CLAIMED_REGIONS.lock();
+
+    // The ranges do not overlap, so only the last range that starts before the end of the new
+    // range can overlap with it.
+    if let Some((start, (end, existing_owner))) = regions.range(..range.end).next_back()
+        && *end > range.start
+    {
+        log::warn!(
+            "{} cannot claim the MMIO region {:#x?}, which overlaps with {:#x?} of {}",
+            owner,
+            range,
+            *start..*end,
+            existing_owner
+        );
+        return Err(Error::AccessDenied);
+    }
+
+    regions.insert(range.start, (range.end, owner.to_string()));
+    Ok(())
+}
+
+fn unregister(range: &Range<Paddr>) {
+    CLAIMED_REGIONS.lock().remove(&range.start);
+}
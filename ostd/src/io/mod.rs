@@ -6,11 +6,18 @@
 //! through _allocators_. There are two types of device I/O:
 //!  - `IoMem` for memory I/O (MMIO).
 //!  - `IoPort` for port I/O (PIO).
+//!
+//! The MMIO ranges of the devices should be claimed as [`MmioRegion`]s, which detect the
+//! conflicting claims of the drivers.
 
 mod io_mem;
+mod mmio_region;
 
-pub use self::io_mem::IoMem;
 pub(crate) use self::io_mem::IoMemAllocatorBuilder;
+pub use self::{
+    io_mem::IoMem,
+    mmio_region::{claimed_regions, MmioReg, MmioRegion},
+};
 
 /// Initializes the static allocator based on builder.
 ///
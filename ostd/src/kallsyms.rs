@@ -0,0 +1,379 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The symbol table of the kernel (kallsyms).
+//!
+//! The ELF symbol table of the kernel is neither loaded into the memory nor kept in the
+//! stripped images. So the kernel image reserves the `.kallsyms` section, which OSDK fills
+//! with a compressed table of the functions and the objects after the kernel is linked. The
+//! table resolves the addresses to the names (see [`symbol_for_addr`]) so that the
+//! diagnostics (e.g., the stack traces) show the function names instead of raw addresses.
+//! The symbols of the loaded kernel extensions (see [`crate::module`]) are resolved, too.
+//!
+//! The table consists of (all integers are little-endian)
+//!  - the header: the magic `KSYM` (`u32`), the number of the symbols (`u32`) and the base
+//!    address (`u64`);
+//!  - the offsets from the base address of the symbols in the ascending order (`[u32]`);
+//!  - the sizes of the symbols (`[u32]`);
+//!  - the offsets in the names of every [`MARKER_INTERVAL`]th symbol (`[u32]`);
+//!  - the names. Each name is encoded as its kind (`u8`, which is a type letter of `nm`),
+//!    the length of the prefix that it shares with the previous name (`u8`, which is zero at
+//!    each marker), the length of the rest (`u8`) and the rest of the name.
+//!
+//! The format must be kept in sync with OSDK.
+
+use alloc::sync::Arc;
+use core::{fmt, mem::size_of};
+
+use crate::{
+    mm::Vaddr,
+    module::{self, Module},
+};
+
+/// The maximum length of a symbol name in bytes. The longer names are truncated.
+pub const MAX_NAME_LEN: usize = 255;
+
+const MAGIC: u32 = u32::from_le_bytes(*b"KSYM");
+const HEADER_SIZE: usize = 16;
+const MARKER_INTERVAL: usize = 64;
+
+/// The size of the area reserved for the symbol table.
+const AREA_SIZE: usize = 4 * 1024 * 1024;
+
+#[repr(C, align(8))]
+struct Area([u8; AREA_SIZE]);
+
+/// The area that OSDK fills with the symbol table.
+#[used]
+#[link_section = ".kallsyms"]
+static AREA: Area = Area([0; AREA_SIZE]);
+
+/// A symbol of the kernel or a loaded kernel extension.
+pub struct Symbol {
+    name: [u8; MAX_NAME_LEN],
+    name_len: usize,
+    addr: Vaddr,
+    size: usize,
+    kind: u8,
+    module: Option<Arc<Module>>,
+}
+
+impl Symbol {
+    /// Returns the (demangled) name of the symbol.
+    pub fn name(&self) -> &str {
+        core::str::from_utf8(&self.name[..self.name_len]).unwrap_or("<invalid>")
+    }
+
+    /// Returns the start address of the symbol.
+    pub fn addr(&self) -> Vaddr {
+        self.addr
+    }
+
+    /// Returns the size of the symbol in bytes, which may be zero if it is unknown.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the type of the symbol as `nm` shows (e.g., `T` for a global function and `d`
+    /// for a local object).
+    pub fn kind(&self) -> char {
+        self.kind as char
+    }
+
+    /// Returns the name of the kernel extension that defines the symbol, or `None` if the
+    /// symbol is defined by the kernel.
+    pub fn module_name(&self) -> Option<&str> {
+        self.module.as_ref().map(|module| module.name())
+    }
+
+    fn new_in_module(module: &Arc<Module>, symbol: &module::ModuleSymbol) -> Self {
+        let mut this = Self {
+            name: [0; MAX_NAME_LEN],
+            name_len: 0,
+            addr: symbol.addr,
+            size: symbol.size,
+            kind: b't',
+            module: Some(module.clone()),
+        };
+        let mut len = symbol.name.len().min(MAX_NAME_LEN);
+        while !symbol.name.is_char_boundary(len) {
+            len -= 1;
+        }
+        this.name[..len].copy_from_slice(&symbol.name.as_bytes()[..len]);
+        this.name_len = len;
+        this
+    }
+}
+
+/// Finds the symbol that contains the address.
+///
+/// If the size of a symbol is unknown, it is assumed to extend to the next symbol.
+///
+/// The function does not sleep, so it can be called in any context (e.g., in the panic
+/// handler). But the symbols of the kernel extensions may not be found if the extensions are
+/// being loaded or unloaded at the same time.
+pub fn symbol_for_addr(addr: Vaddr) -> Option<Symbol> {
+    if let Some(table) = Table::get() {
+        if let Some(symbol) = table.symbol_for_addr(addr) {
+            return Some(symbol);
+        }
+    }
+
+    let module = module::try_find_module(addr)?;
+    let symbol = module
+        .symbols()
+        .iter()
+        .filter(|symbol| symbol.addr <= addr && addr < symbol.addr + symbol.size.max(1))
+        .max_by_key(|symbol| symbol.addr)?;
+    Some(Symbol::new_in_module(&module, symbol))
+}
+
+/// Returns the address of the symbol with the name.
+///
+/// The kernel symbols are preferred to the symbols of the kernel extensions.
+pub fn addr_of_symbol(name: &str) -> Option<Vaddr> {
+    let mut addr = None;
+    if let Some(table) = Table::get() {
+        table.for_each(|symbol| {
+            if addr.is_none() && symbol.name() == name {
+                addr = Some(symbol.addr);
+            }
+        });
+    }
+    addr.or_else(|| {
+        module::modules().iter().find_map(|module| {
+            let symbol = module.symbols().iter().find(|symbol| symbol.name == name)?;
+            Some(symbol.addr)
+        })
+    })
+}
+
+/// Calls the function with each symbol of the kernel and the loaded kernel extensions.
+///
+/// The kernel symbols are visited in the ascending order of their addresses.
+pub fn for_each_symbol(mut f: impl FnMut(&Symbol)) {
+    if let Some(table) = Table::get() {
+        table.for_each(&mut f);
+    }
+    for module in module::modules() {
+        for symbol in module.symbols() {
+            f(&Symbol::new_in_module(&module, symbol));
+        }
+    }
+}
+
+/// An address that is formatted with its symbol.
+///
+/// The address is shown as `name+offset/size`, or in hexadecimal if it is not in any symbol.
+///
+/// # Examples
+///
+/// ```
+/// use ostd::kallsyms::SymbolizedAddr;
+///
+/// let addr = ostd::kallsyms::addr_of_symbol as usize;
+/// // E.g., "ostd::kallsyms::addr_of_symbol+0x4/0x1a6".
+/// ostd::early_println!("{}", SymbolizedAddr(addr + 4));
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct SymbolizedAddr(pub Vaddr);
+
+impl fmt::Display for SymbolizedAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(symbol) = symbol_for_addr(self.0) else {
+            return write!(f, "{:#x}", self.0);
+        };
+        write!(
+            f,
+            "{}+{:#x}/{:#x}",
+            symbol.name(),
+            self.0 - symbol.addr,
+            symbol.size
+        )?;
+        if let Some(module_name) = symbol.module_name() {
+            write!(f, " [{}]", module_name)?;
+        }
+        Ok(())
+    }
+}
+
+/// The symbol table filled by OSDK.
+struct Table {
+    data: &'static [u8],
+    nr_symbols: usize,
+    base: u64,
+}
+
+impl Table {
+    /// Returns the table, or `None` if OSDK has not filled it.
+    fn get() -> Option<Self> {
+        extern "C" {
+            fn __kallsyms();
+            fn __kallsyms_end();
+        }
+
+        // The area is accessed with the symbols from the linker, since the compiler assumes
+        // that the area is all zeros as it is initialized.
+        let start = __kallsyms as usize;
+        let len = __kallsyms_end as usize - start;
+        // SAFETY: The area is in the kernel image and is immutable.
+        let data = unsafe { core::slice::from_raw_parts(start as *const u8, len) };
+
+        let table = Self {
+            data,
+            nr_symbols: read_u32(data, 4)? as usize,
+            base: read_u64(data, 8)?,
+        };
+        if read_u32(data, 0)? != MAGIC || table.names_start() > data.len() {
+            return None;
+        }
+        Some(table)
+    }
+
+    fn offset(&self, index: usize) -> u64 {
+        read_u32(self.data, HEADER_SIZE + index * size_of::<u32>()).unwrap_or(0) as u64
+    }
+
+    fn size(&self, index: usize) -> usize {
+        let sizes_start = HEADER_SIZE + self.nr_symbols * size_of::<u32>();
+        read_u32(self.data, sizes_start + index * size_of::<u32>()).unwrap_or(0) as usize
+    }
+
+    fn markers_start(&self) -> usize {
+        HEADER_SIZE + self.nr_symbols * size_of::<u32>() * 2
+    }
+
+    fn names_start(&self) -> usize {
+        let nr_markers = self.nr_symbols.div_ceil(MARKER_INTERVAL);
+        self.markers_start() + nr_markers * size_of::<u32>()
+    }
+
+    fn symbol_for_addr(&self, addr: Vaddr) -> Option<Symbol> {
+        let offset = (addr as u64).checked_sub(self.base)?;
+        // The index of the last symbol that starts at or before the address.
+        let nr_before = partition_point(self.nr_symbols, |index| self.offset(index) <= offset);
+        let index = nr_before.checked_sub(1)?;
+
+        let start = self.offset(index);
+        let size = self.size(index) as u64;
+        let end = if size > 0 {
+            start + size
+        } else if index + 1 < self.nr_symbols {
+            self.offset(index + 1)
+        } else {
+            start + 1
+        };
+        if offset >= end {
+            return None;
+        }
+
+        let marker = index / MARKER_INTERVAL;
+        let marker_offset = read_u32(self.data, self.markers_start() + marker * size_of::<u32>())?;
+        let mut pos = self.names_start() + marker_offset as usize;
+        let mut symbol = self.new_symbol(index);
+        for _ in marker * MARKER_INTERVAL..=index {
+            pos = self.decode_name(pos, &mut symbol)?;
+        }
+        Some(symbol)
+    }
+
+    fn for_each(&self, mut f: impl FnMut(&Symbol)) {
+        let mut pos = self.names_start();
+        let mut symbol = self.new_symbol(0);
+        for index in 0..self.nr_symbols {
+            symbol.addr = (self.base + self.offset(index)) as Vaddr;
+            symbol.size = self.size(index);
+            let Some(next_pos) = self.decode_name(pos, &mut symbol) else {
+                return;
+            };
+            pos = next_pos;
+            f(&symbol);
+        }
+    }
+
+    fn new_symbol(&self, index: usize) -> Symbol {
+        Symbol {
+            name: [0; MAX_NAME_LEN],
+            name_len: 0,
+            addr: (self.base + self.offset(index)) as Vaddr,
+            size: self.size(index),
+            kind: b'?',
+            module: None,
+        }
+    }
+
+    /// Decodes the name at the position into the symbol, which holds the previous name.
+    ///
+    /// Returns the position of the next name.
+    fn decode_name(&self, pos: usize, symbol: &mut Symbol) -> Option<usize> {
+        let header = self.data.get(pos..pos + 3)?;
+        let (kind, prefix_len, rest_len) = (header[0], header[1] as usize, header[2] as usize);
+        let rest = self.data.get(pos + 3..pos + 3 + rest_len)?;
+        if prefix_len > symbol.name_len || prefix_len + rest_len > MAX_NAME_LEN {
+            return None;
+        }
+        symbol.name[prefix_len..prefix_len + rest_len].copy_from_slice(rest);
+        symbol.name_len = prefix_len + rest_len;
+        symbol.kind = kind;
+        Some(pos + 3 + rest_len)
+    }
+}
+
+/// Returns the number of the leading indices in `0..len` that satisfy the predicate, which
+/// must be satisfied by a prefix of the indices.
+fn partition_point(len: usize, pred: impl Fn(usize) -> bool) -> usize {
+    let (mut low, mut high) = (0, len);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if pred(mid) {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+    low
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset.checked_add(8)?)?;
+    Some(u64::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+#[cfg(ktest)]
+mod test {
+    use super::*;
+    use crate::prelude::ktest;
+
+    #[ktest]
+    fn resolve_kernel_function() {
+        assert!(Table::get().is_some(), "the symbol table is not filled");
+
+        let addr = addr_of_symbol as usize;
+        let symbol = symbol_for_addr(addr + 1).unwrap();
+        assert!(symbol.name().ends_with("kallsyms::addr_of_symbol"));
+        assert_eq!(symbol.addr(), addr);
+        assert_eq!(symbol.kind().to_ascii_lowercase(), 't');
+        assert!(symbol.module_name().is_none());
+
+        assert_eq!(addr_of_symbol(symbol.name()), Some(addr));
+        assert_eq!(addr_of_symbol("no such symbol"), None);
+    }
+
+    #[ktest]
+    fn symbols_are_sorted() {
+        let mut last_addr = 0;
+        let mut nr_symbols = 0;
+        for_each_symbol(|symbol| {
+            if symbol.module_name().is_none() {
+                assert!(symbol.addr() >= last_addr);
+                last_addr = symbol.addr();
+                nr_symbols += 1;
+            }
+        });
+        assert!(nr_symbols > 0);
+    }
+}
@@ -33,14 +33,18 @@ pub mod console;
 pub mod cpu;
 mod error;
 pub mod io;
+pub mod kallsyms;
 pub mod logger;
 pub mod mm;
+pub mod module;
 pub mod panic;
+pub mod power;
 pub mod prelude;
 pub mod smp;
 pub mod sync;
 pub mod task;
 pub mod timer;
+pub mod trace;
 pub mod trap;
 pub mod user;
 pub(crate) mod util;
@@ -119,6 +123,10 @@ unsafe fn init() {
         mm::kspace::activate_kernel_page_table();
     }
 
+    // The trace buffer is virtually allocated, so it is accessible only after the kernel page
+    // table is activated.
+    trace::init();
+
     bus::init();
 
     arch::irq::enable_local();
@@ -37,7 +37,7 @@ struct DmaStreamInner {
     segment: USegment,
     start_daddr: Daddr,
     /// TODO: remove this field when on x86.
-    #[expect(unused)]
+    #[cfg_attr(target_arch = "x86_64", expect(unused))]
     is_cache_coherent: bool,
     direction: DmaDirection,
 }
@@ -149,44 +149,32 @@ impl DmaStream {
                 // The streaming DMA mapping in x86_64 is cache coherent, and does not require synchronization.
                 // Reference: <https://lwn.net/Articles/855328/>, <https://lwn.net/Articles/2265/>
                 Ok(())
-            } else {
+            } else if #[cfg(target_arch = "riscv64")] {
+                use crate::arch::mm::cache::{sync_range, CacheOp};
+
                 if _byte_range.end > self.nbytes() {
                     return Err(Error::InvalidArgs);
                 }
                 if self.inner.is_cache_coherent {
                     return Ok(());
                 }
-                let start_va = crate::mm::paddr_to_vaddr(self.inner.segment.paddr()) as *const u8;
-                // TODO: Query the CPU for the cache line size via CPUID, we use 64 bytes as the cache line size here.
-                // for i in _byte_range.step_by(64) {
-                //     // TODO: Call the cache line flush command in the corresponding architecture.
-                //     todo!()
-                // }
-                // Ok(())
-                #[cfg(target_arch = "riscv64")]
-            {
-                for i in _byte_range.step_by(64) {
-                    let addr = unsafe { start_va.add(i) };
-                    // 此处调用 fence 指令来确保内存操作顺序，
-                    // 如果你的 RISC-V 平台提供专用的缓存刷新指令，请将下面这行替换掉
-                    unsafe {
-                        core::arch::asm!(
-                            "fence rw, rw",
-                            options(nostack, preserves_flags)
-                        );
-                    }
-                    // 可以考虑打印调试信息
-                    // debug!("Flushed cache line at address: {:p}", addr);
+                let start_va = crate::mm::paddr_to_vaddr(self.inner.segment.start_paddr());
+                let op = match self.inner.direction {
+                    DmaDirection::ToDevice => CacheOp::Clean,
+                    DmaDirection::FromDevice => CacheOp::Invalidate,
+                    DmaDirection::Bidirectional => CacheOp::Flush,
+                };
+                sync_range(start_va + _byte_range.start..start_va + _byte_range.end, op);
+                Ok(())
+            } else {
+                if _byte_range.end > self.nbytes() {
+                    return Err(Error::InvalidArgs);
                 }
-            }
-            // 如果不是 riscv64 的其它平台，可以添加相应的实现
-            #[cfg(not(target_arch = "riscv64"))]
-            {
-                for _ in _byte_range.step_by(64) {
-                    // 占位，不做任何操作
+                if self.inner.is_cache_coherent {
+                    return Ok(());
                 }
-            }
-            Ok(())
+                // TODO: Call the cache line flush command in the corresponding architecture.
+                todo!()
             }
         }
     }
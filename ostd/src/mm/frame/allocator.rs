@@ -2,18 +2,14 @@
 
 //! The physical memory allocator.
 
-use core::{alloc::Layout, ops::Range};
+use core::alloc::Layout;
 
-use align_ext::AlignExt;
-
-use super::{meta::AnyFrameMeta, segment::Segment, Frame};
+use super::{memblock::Memblock, meta::AnyFrameMeta, segment::Segment, Frame};
 use crate::{
-    boot::memory_region::MemoryRegionType,
     error::Error,
     impl_frame_meta_for,
     mm::{paddr_to_vaddr, Paddr, PAGE_SIZE},
     prelude::*,
-    util::range_difference,
 };
 
 /// Options for allocating physical memory frames.
@@ -197,42 +193,17 @@ pub(super) fn get_global_frame_allocator() -> &'static dyn GlobalFrameAllocator
 ///
 /// This function should be called only once.
 pub(crate) unsafe fn init() {
-    let regions = &crate::boot::EARLY_INFO.get().unwrap().memory_regions;
-
     // Retire the early allocator.
     let early_allocator = EARLY_ALLOCATOR.lock().take().unwrap();
-    let (range_1, range_2) = early_allocator.allocated_regions();
 
-    for region in regions.iter() {
-        if region.typ() == MemoryRegionType::Usable {
-            debug_assert!(region.base() % PAGE_SIZE == 0);
-            debug_assert!(region.len() % PAGE_SIZE == 0);
-
-            // Add global free pages to the frame allocator.
-            // Truncate the early allocated frames if there is an overlap.
-            for r1 in range_difference(&(region.base()..region.end()), &range_1) {
-                for r2 in range_difference(&r1, &range_2) {
-                    log::info!("Adding free frames to the allocator: {:x?}", r2);
-                    get_global_frame_allocator().add_free_memory(r2.start, r2.len());
-                }
-            }
-        }
+    // Add global free pages to the frame allocator, which are exactly the usable memory that is
+    // not reserved in the early boot phase.
+    for range in early_allocator.free_ranges() {
+        log::info!("Adding free frames to the allocator: {:x?}", range);
+        get_global_frame_allocator().add_free_memory(range.start, range.len());
     }
 }
 
-/// An allocator in the early boot phase when frame metadata is not available.
-pub(super) struct EarlyFrameAllocator {
-    // We need to allocate from under 4G first since the linear mapping for
-    // the higher region is not constructed yet.
-    under_4g_range: Range<Paddr>,
-    under_4g_end: Paddr,
-
-    // And also sometimes 4G is not enough for early phase. This, if not `0..0`,
-    // is the largest region above 4G.
-    max_range: Range<Paddr>,
-    max_end: Paddr,
-}
-
 /// The global frame allocator in the early boot phase.
 ///
 /// It is used to allocate frames before the frame metadata is initialized.
@@ -244,83 +215,7 @@ pub(super) struct EarlyFrameAllocator {
 /// since the latter uses CPU-local storage, which isn't available in the early
 /// boot phase. So we must make sure that no interrupts are enabled when using
 /// this allocator.
-pub(super) static EARLY_ALLOCATOR: spin::Mutex<Option<EarlyFrameAllocator>> =
-    spin::Mutex::new(None);
-
-impl EarlyFrameAllocator {
-    /// Creates a new early frame allocator.
-    ///
-    /// It uses at most 2 regions, the first is the maximum usable region below
-    /// 4 GiB. The other is the maximum usable region above 4 GiB and is only
-    /// usable when linear mapping is constructed.
-    pub fn new() -> Self {
-        let regions = &crate::boot::EARLY_INFO.get().unwrap().memory_regions;
-
-        let mut under_4g_range = 0..0;
-        let mut max_range = 0..0;
-        for region in regions.iter() {
-            if region.typ() != MemoryRegionType::Usable {
-                continue;
-            }
-            const PADDR4G: Paddr = 0x1_0000_0000;
-            if region.base() < PADDR4G {
-                let range = region.base()..region.end().min(PADDR4G);
-                if range.len() > under_4g_range.len() {
-                    under_4g_range = range;
-                }
-            }
-            if region.end() >= PADDR4G {
-                let range = region.base().max(PADDR4G)..region.end();
-                if range.len() > max_range.len() {
-                    max_range = range;
-                }
-            }
-        }
-
-        log::debug!(
-            "Early frame allocator (below 4G) at: {:#x?}",
-            under_4g_range
-        );
-        if !max_range.is_empty() {
-            log::debug!("Early frame allocator (above 4G) at: {:#x?}", max_range);
-        }
-
-        Self {
-            under_4g_range: under_4g_range.clone(),
-            under_4g_end: under_4g_range.start,
-            max_range: max_range.clone(),
-            max_end: max_range.start,
-        }
-    }
-
-    /// Allocates a contiguous range of frames.
-    pub fn alloc(&mut self, layout: Layout) -> Option<Paddr> {
-        let size = layout.size().align_up(PAGE_SIZE);
-        let align = layout.align().max(PAGE_SIZE);
-
-        for (tail, end) in [
-            (&mut self.under_4g_end, self.under_4g_range.end),
-            (&mut self.max_end, self.max_range.end),
-        ] {
-            let allocated = tail.align_up(align);
-            if let Some(allocated_end) = allocated.checked_add(size)
-                && allocated_end <= end
-            {
-                *tail = allocated_end;
-                return Some(allocated);
-            }
-        }
-
-        None
-    }
-
-    pub(super) fn allocated_regions(&self) -> (Range<Paddr>, Range<Paddr>) {
-        (
-            self.under_4g_range.start..self.under_4g_end,
-            self.max_range.start..self.max_end,
-        )
-    }
-}
+pub(super) static EARLY_ALLOCATOR: spin::Mutex<Option<Memblock>> = spin::Mutex::new(None);
 
 /// Metadata for frames allocated in the early boot phase.
 ///
@@ -356,6 +251,7 @@ pub(crate) fn early_alloc(layout: Layout) -> Option<Paddr> {
 ///
 /// This function should be called only once after the memory regions are ready.
 pub(crate) unsafe fn init_early_allocator() {
+    let regions = &crate::boot::EARLY_INFO.get().unwrap().memory_regions;
     let mut early_allocator = EARLY_ALLOCATOR.lock();
-    *early_allocator = Some(EarlyFrameAllocator::new());
+    *early_allocator = Some(Memblock::new(regions));
 }
@@ -0,0 +1,263 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The memblock, which allocates physical memory in the early boot phase.
+//!
+//! Before the frame metadata and the global frame allocator are initialized, the physical memory
+//! is allocated from the memblock, as the memblock of Linux does. The memblock is seeded with the
+//! usable memory regions, which are parsed from the memory map of the bootloader or the firmware
+//! (e.g., the memory nodes of the device tree on RISC-V), and tracks the reserved ranges, i.e.,
+//! the ranges that are allocated during the early boot phase.
+//!
+//! When the global frame allocator is initialized, the memblock is retired:
+//!  - the reserved ranges are tracked with the frame metadata (see [`super::meta::init`]);
+//!  - exactly the rest of the usable memory is added to the global frame allocator (see
+//!    [`super::allocator::init`]).
+//!
+//! The memblock does not use the heap, so the ranges are kept in arrays of fixed capacities.
+
+use core::{alloc::Layout, ops::Range};
+
+use align_ext::AlignExt;
+
+use crate::{
+    boot::memory_region::{MemoryRegionArray, MemoryRegionType, MAX_REGIONS},
+    mm::{Paddr, PAGE_SIZE},
+};
+
+/// The maximum number of the reserved ranges.
+///
+/// The adjacent reserved ranges are merged, and the allocations are mostly adjacent, so the
+/// number is usually small.
+const MAX_RESERVED_RANGES: usize = 128;
+
+/// The end of the physical memory that is linearly mapped by the boot page table.
+///
+/// The memory below it is preferred, since the linear mapping of the higher memory is not
+/// constructed until the frame metadata is initialized.
+const PADDR4G: Paddr = 0x1_0000_0000;
+
+/// The memblock, which is an early allocator of the physical memory.
+pub(super) struct Memblock {
+    memory: RangeArray<MAX_REGIONS>,
+    reserved: RangeArray<MAX_RESERVED_RANGES>,
+}
+
+impl Memblock {
+    /// Creates a memblock with the usable memory regions.
+    pub(super) fn new(regions: &MemoryRegionArray) -> Self {
+        let mut memory = RangeArray::new();
+        for region in regions.iter() {
+            if region.typ() != MemoryRegionType::Usable {
+                continue;
+            }
+            debug_assert!(region.base() % PAGE_SIZE == 0);
+            debug_assert!(region.len() % PAGE_SIZE == 0);
+            memory.insert(region.base()..region.end()).unwrap();
+        }
+
+        for range in memory.iter() {
+            log::debug!("Memblock memory: {:#x?}", range);
+        }
+
+        Self {
+            memory,
+            reserved: RangeArray::new(),
+        }
+    }
+
+    /// Allocates a contiguous range of frames, whose address is as low as possible.
+    ///
+    /// The memory below 4 GiB is allocated first. The memory above 4 GiB is used only if the
+    /// former is exhausted, and is only accessible when the linear mapping is constructed.
+    pub(super) fn alloc(&mut self, layout: Layout) -> Option<Paddr> {
+        let size = layout.size().align_up(PAGE_SIZE);
+        let align = layout.align().max(PAGE_SIZE);
+
+        let start = self
+            .find_free(size, align, PADDR4G)
+            .or_else(|| self.find_free(size, align, Paddr::MAX))?;
+        self.reserved.insert(start..start + size).ok()?;
+        Some(start)
+    }
+
+    /// Returns the reserved ranges, in the ascending order.
+    pub(super) fn reserved(&self) -> impl Iterator<Item = Range<Paddr>> + '_ {
+        self.reserved.iter()
+    }
+
+    /// Returns the usable memory that is not reserved, in the ascending order.
+    pub(super) fn free_ranges(&self) -> impl Iterator<Item = Range<Paddr>> + '_ {
+        self.memory.iter().flat_map(|memory| self.gaps(memory))
+    }
+
+    fn find_free(&self, size: usize, align: usize, limit: Paddr) -> Option<Paddr> {
+        self.free_ranges().find_map(|free| {
+            let start = free.start.align_up(align);
+            let end = start.checked_add(size)?;
+            (end <= free.end.min(limit)).then_some(start)
+        })
+    }
+
+    /// Returns the parts of the range that are not reserved.
+    fn gaps(&self, range: Range<Paddr>) -> impl Iterator<Item = Range<Paddr>> + '_ {
+        let Range { start, end } = range;
+        let mut cursor = start;
+        let mut reserved = self
+            .reserved
+            .iter()
+            .skip_while(move |reserved| reserved.end <= start);
+        core::iter::from_fn(move || {
+            while cursor < end {
+                let Some(next) = reserved.next() else {
+                    let gap = cursor..end;
+                    cursor = end;
+                    return Some(gap);
+                };
+                let gap = cursor..next.start.min(end);
+                cursor = cursor.max(next.end);
+                if !gap.is_empty() {
+                    return Some(gap);
+                }
+            }
+            None
+        })
+    }
+}
+
+/// Sorted, non-overlapping and non-adjacent ranges in an array.
+struct RangeArray<const N: usize> {
+    ranges: [Range<Paddr>; N],
+    len: usize,
+}
+
+impl<const N: usize> RangeArray<N> {
+    const fn new() -> Self {
+        Self {
+            ranges: [const { 0..0 }; N],
+            len: 0,
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = Range<Paddr>> + '_ {
+        self.ranges[..self.len].iter().cloned()
+    }
+
+    /// Inserts a range, which is merged with the overlapping and the adjacent ranges.
+    ///
+    /// If the array is full, an error is returned.
+    fn insert(&mut self, range: Range<Paddr>) -> Result<(), ()> {
+        if range.is_empty() {
+            return Ok(());
+        }
+
+        // The ranges in `first..last` are merged with the new range.
+        let first = self.ranges[..self.len].partition_point(|r| r.end < range.start);
+        let last = first + self.ranges[first..self.len].partition_point(|r| r.start <= range.end);
+        if first == last {
+            if self.len == N {
+                return Err(());
+            }
+            self.ranges[first..=self.len].rotate_right(1);
+            self.ranges[first] = range;
+            self.len += 1;
+            return Ok(());
+        }
+
+        let merged =
+            range.start.min(self.ranges[first].start)..range.end.max(self.ranges[last - 1].end);
+        self.ranges[first] = merged;
+        self.ranges[first + 1..self.len].rotate_left(last - first - 1);
+        self.len -= last - first - 1;
+        Ok(())
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::{boot::memory_region::MemoryRegion, prelude::ktest};
+
+    fn memblock(usable: &[Range<Paddr>]) -> Memblock {
+        let mut regions = MemoryRegionArray::new();
+        for range in usable {
+            regions
+                .push(MemoryRegion::new(
+                    range.start,
+                    range.len(),
+                    MemoryRegionType::Usable,
+                ))
+                .unwrap();
+        }
+        Memblock::new(&regions)
+    }
+
+    fn page_layout(nr_pages: usize, align: usize) -> Layout {
+        Layout::from_size_align(nr_pages * PAGE_SIZE, align).unwrap()
+    }
+
+    #[ktest]
+    fn range_array_insert() {
+        let mut ranges = RangeArray::<3>::new();
+        ranges.insert(0x3000..0x4000).unwrap();
+        ranges.insert(0x1000..0x2000).unwrap();
+        ranges.insert(0x8000..0x9000).unwrap();
+        assert_eq!(
+            ranges.iter().collect::<Vec<_>>(),
+            [0x1000..0x2000, 0x3000..0x4000, 0x8000..0x9000]
+        );
+
+        // The adjacent ranges are merged.
+        ranges.insert(0x2000..0x3000).unwrap();
+        assert_eq!(
+            ranges.iter().collect::<Vec<_>>(),
+            [0x1000..0x4000, 0x8000..0x9000]
+        );
+        // The overlapping ranges are merged.
+        ranges.insert(0x3800..0x8800).unwrap();
+        assert_eq!(ranges.iter().collect::<Vec<_>>(), [0x1000..0x9000]);
+
+        ranges.insert(0xa000..0xb000).unwrap();
+        ranges.insert(0xc000..0xd000).unwrap();
+        assert!(ranges.insert(0xe000..0xf000).is_err());
+        ranges.insert(0xb000..0xc000).unwrap();
+        assert_eq!(
+            ranges.iter().collect::<Vec<_>>(),
+            [0x1000..0x9000, 0xa000..0xd000]
+        );
+    }
+
+    #[ktest]
+    fn alloc_skips_reserved() {
+        let mut memblock = memblock(&[0x10_0000..0x10_4000, 0x20_0000..0x40_0000]);
+
+        let first = memblock.alloc(page_layout(2, PAGE_SIZE)).unwrap();
+        assert_eq!(first, 0x10_0000);
+        let second = memblock.alloc(page_layout(1, PAGE_SIZE)).unwrap();
+        assert_eq!(second, 0x10_2000);
+        // The rest of the first region is too small.
+        let third = memblock.alloc(page_layout(2, PAGE_SIZE)).unwrap();
+        assert_eq!(third, 0x20_0000);
+        let aligned = memblock.alloc(page_layout(1, 0x10_0000)).unwrap();
+        assert_eq!(aligned, 0x30_0000);
+
+        assert_eq!(
+            memblock.reserved().collect::<Vec<_>>(),
+            [
+                0x10_0000..0x10_3000,
+                0x20_0000..0x20_2000,
+                0x30_0000..0x30_1000
+            ]
+        );
+        assert_eq!(
+            memblock.free_ranges().collect::<Vec<_>>(),
+            [
+                0x10_3000..0x10_4000,
+                0x20_2000..0x30_0000,
+                0x30_1000..0x40_0000
+            ]
+        );
+        assert!(memblock.alloc(page_layout(0x200, PAGE_SIZE)).is_none());
+    }
+}
@@ -13,6 +13,9 @@
 //! The slots are placed in the metadata pages mapped to a certain virtual
 //! address in the kernel space. So finding the metadata of a frame often
 //! comes with no costs since the translation is a simple arithmetic operation.
+//!
+//! The array is sparse (see [`vmemmap`]), so the holes in the physical
+//! address space cost no metadata pages.
 
 pub(crate) mod mapping {
     //! The metadata of each physical page is linear mapped to fixed virtual addresses
@@ -38,6 +41,86 @@ pub(crate) mod mapping {
     }
 }
 
+pub(crate) mod vmemmap {
+    //! The sparse virtual memory map (vmemmap) of the frame metadata.
+    //!
+    //! The slots are indexed by the frame number in [`FRAME_METADATA_RANGE`],
+    //! but the range is populated sparsely as the `SPARSEMEM_VMEMMAP` of Linux
+    //! does. The physical address space is divided into sections, and only the
+    //! metadata pages of the present sections, i.e., the sections that contain
+    //! physical memory, are allocated and mapped. For example, the DRAM starts
+    //! at 2 GiB on most RISC-V platforms, but the 2 GiB below it costs nothing.
+    //!
+    //! [`FRAME_METADATA_RANGE`]: crate::mm::kspace::FRAME_METADATA_RANGE
+
+    use core::{alloc::Layout, mem::size_of, ops::Range};
+
+    use spin::Once;
+
+    use super::MetaSlot;
+    use crate::{
+        boot::memory_region::{MemoryRegionArray, MemoryRegionType},
+        mm::{frame::allocator, paddr_to_vaddr, Paddr, PAGE_SIZE},
+    };
+
+    /// The size of the physical memory of a section, which is 128 MiB.
+    pub(crate) const SECTION_SIZE: usize = 1 << 27;
+    /// The size of the metadata pages of a section.
+    pub(crate) const SECTION_META_SIZE: usize = SECTION_SIZE / PAGE_SIZE * size_of::<MetaSlot>();
+
+    const BITS_PER_WORD: usize = u64::BITS as usize;
+
+    /// The bitmap of the present sections.
+    static PRESENT_SECTIONS: Once<&'static [u64]> = Once::new();
+
+    /// Finds the present sections below `max_paddr`.
+    ///
+    /// A section is present if it overlaps with any of the memory regions
+    /// other than the holes (i.e., [`MemoryRegionType::Unknown`]).
+    pub(super) fn init(regions: &MemoryRegionArray, max_paddr: Paddr) {
+        let nr_words = max_paddr.div_ceil(SECTION_SIZE).div_ceil(BITS_PER_WORD);
+        let bitmap_paddr = allocator::early_alloc(Layout::array::<u64>(nr_words).unwrap()).unwrap();
+        // SAFETY: The memory is just allocated with `nr_words` words and is
+        // linearly mapped, so we have exclusive access to it.
+        let bitmap = unsafe {
+            core::slice::from_raw_parts_mut(paddr_to_vaddr(bitmap_paddr) as *mut u64, nr_words)
+        };
+        bitmap.fill(0);
+
+        for region in regions
+            .iter()
+            .filter(|r| r.typ() != MemoryRegionType::Unknown)
+        {
+            let start = region.base().min(max_paddr) / SECTION_SIZE;
+            let end = region.end().min(max_paddr).div_ceil(SECTION_SIZE);
+            for section in start..end {
+                bitmap[section / BITS_PER_WORD] |= 1 << (section % BITS_PER_WORD);
+            }
+        }
+
+        PRESENT_SECTIONS.call_once(|| bitmap);
+    }
+
+    /// Returns whether the metadata slot of the physical address is populated.
+    pub(crate) fn is_present(paddr: Paddr) -> bool {
+        let section = paddr / SECTION_SIZE;
+        PRESENT_SECTIONS
+            .get()
+            .and_then(|bitmap| bitmap.get(section / BITS_PER_WORD))
+            .is_some_and(|word| word & (1 << (section % BITS_PER_WORD)) != 0)
+    }
+
+    /// Returns the physical memory ranges of the present sections, in the ascending order.
+    pub(crate) fn present_sections() -> impl Iterator<Item = Range<Paddr>> {
+        let bitmap: &'static [u64] = PRESENT_SECTIONS.get().copied().unwrap_or_default();
+        (0..bitmap.len() * BITS_PER_WORD)
+            .filter(move |section| {
+                bitmap[section / BITS_PER_WORD] & (1 << (section % BITS_PER_WORD)) != 0
+            })
+            .map(|section| section * SECTION_SIZE..(section + 1) * SECTION_SIZE)
+    }
+}
+
 use core::{
     alloc::Layout,
     any::Any,
@@ -53,7 +136,7 @@ use log::info;
 
 use crate::{
     arch::mm::PagingConsts,
-    boot::memory_region::MemoryRegionType,
+    boot::memory_region::{MemoryRegion, MemoryRegionType},
     const_assert,
     mm::{
         frame::allocator::{self, EarlyAllocatedFrameMeta},
@@ -206,7 +289,7 @@ pub(super) fn get_slot(paddr: Paddr) -> Result<&'static MetaSlot, GetFrameError>
     if paddr % PAGE_SIZE != 0 {
         return Err(GetFrameError::NotAligned);
     }
-    if paddr >= super::MAX_PADDR.load(Ordering::Relaxed) as Paddr {
+    if paddr >= super::MAX_PADDR.load(Ordering::Relaxed) as Paddr || !vmemmap::is_present(paddr) {
         return Err(GetFrameError::OutOfBound);
     }
 
@@ -467,21 +550,28 @@ pub(crate) unsafe fn init() -> Segment<MetaPageMeta> {
 
     add_temp_linear_mapping(max_paddr);
 
-    let tot_nr_frames = max_paddr / page_size::<PagingConsts>(1);
-    let (nr_meta_pages, meta_pages) = alloc_meta_frames(tot_nr_frames);
+    vmemmap::init(
+        &crate::boot::EARLY_INFO.get().unwrap().memory_regions,
+        max_paddr,
+    );
+    let (nr_meta_pages, meta_pages) = alloc_meta_frames();
 
-    // Map the metadata frames.
+    // Map the metadata frames of the present sections.
     boot_pt::with_borrow(|boot_pt| {
-        for i in 0..nr_meta_pages {
-            let frame_paddr = meta_pages + i * PAGE_SIZE;
-            let vaddr = mapping::frame_to_meta::<PagingConsts>(0) + i * PAGE_SIZE;
-            let prop = PageProperty {
-                flags: PageFlags::RW,
-                cache: CachePolicy::Writeback,
-                priv_flags: PrivilegedPageFlags::GLOBAL,
-            };
-            // SAFETY: we are doing the metadata mappings for the kernel.
-            unsafe { boot_pt.map_base_page(vaddr, frame_paddr / PAGE_SIZE, prop) };
+        let prop = PageProperty {
+            flags: PageFlags::RW,
+            cache: CachePolicy::Writeback,
+            priv_flags: PrivilegedPageFlags::GLOBAL,
+        };
+        let mut frame_paddr = meta_pages;
+        for section in vmemmap::present_sections() {
+            let start_vaddr = mapping::frame_to_meta::<PagingConsts>(section.start);
+            for vaddr in (start_vaddr..start_vaddr + vmemmap::SECTION_META_SIZE).step_by(PAGE_SIZE)
+            {
+                // SAFETY: we are doing the metadata mappings for the kernel.
+                unsafe { boot_pt.map_base_page(vaddr, frame_paddr / PAGE_SIZE, prop) };
+                frame_paddr += PAGE_SIZE;
+            }
         }
     })
     .unwrap();
@@ -491,19 +581,14 @@ pub(crate) unsafe fn init() -> Segment<MetaPageMeta> {
 
     let meta_page_range = meta_pages..meta_pages + nr_meta_pages * PAGE_SIZE;
 
-    let (range_1, range_2) = allocator::EARLY_ALLOCATOR
-        .lock()
-        .as_ref()
-        .unwrap()
-        .allocated_regions();
-    for r in range_difference(&range_1, &meta_page_range) {
-        let early_seg = Segment::from_unused(r, |_| EarlyAllocatedFrameMeta).unwrap();
-        let _ = ManuallyDrop::new(early_seg);
-    }
-    for r in range_difference(&range_2, &meta_page_range) {
-        let early_seg = Segment::from_unused(r, |_| EarlyAllocatedFrameMeta).unwrap();
-        let _ = ManuallyDrop::new(early_seg);
+    let early_allocator = allocator::EARLY_ALLOCATOR.lock();
+    for reserved in early_allocator.as_ref().unwrap().reserved() {
+        for r in range_difference(&reserved, &meta_page_range) {
+            let early_seg = Segment::from_unused(r, |_| EarlyAllocatedFrameMeta).unwrap();
+            let _ = ManuallyDrop::new(early_seg);
+        }
     }
+    drop(early_allocator);
 
     mark_unusable_ranges();
 
@@ -518,11 +603,13 @@ pub(in crate::mm) fn is_initialized() -> bool {
     super::MAX_PADDR.load(Ordering::Relaxed) != 0
 }
 
-fn alloc_meta_frames(tot_nr_frames: usize) -> (usize, Paddr) {
-    let nr_meta_pages = tot_nr_frames
-        .checked_mul(size_of::<MetaSlot>())
-        .unwrap()
-        .div_ceil(PAGE_SIZE);
+/// Allocates and initializes the metadata frames of the present sections.
+fn alloc_meta_frames() -> (usize, Paddr) {
+    let nr_meta_pages = vmemmap::present_sections()
+        .count()
+        .checked_mul(vmemmap::SECTION_META_SIZE / PAGE_SIZE)
+        .unwrap();
+    let tot_nr_slots = nr_meta_pages * PAGE_SIZE / size_of::<MetaSlot>();
     let start_paddr = allocator::early_alloc(
         Layout::from_size_align(nr_meta_pages * PAGE_SIZE, PAGE_SIZE).unwrap(),
     )
@@ -531,8 +618,8 @@ fn alloc_meta_frames(tot_nr_frames: usize) -> (usize, Paddr) {
     let slots = paddr_to_vaddr(start_paddr) as *mut MetaSlot;
 
     // Initialize the metadata slots.
-    for i in 0..tot_nr_frames {
-        // SAFETY: The memory is successfully allocated with `tot_nr_frames`
+    for i in 0..tot_nr_slots {
+        // SAFETY: The memory is successfully allocated with `tot_nr_slots`
         // slots so the index must be within the range.
         let slot = unsafe { slots.add(i) };
         // SAFETY: The memory is just allocated so we have exclusive access and
@@ -580,7 +667,17 @@ fn mark_unusable_ranges() {
     {
         match region.typ() {
             MemoryRegionType::BadMemory => mark_ranges!(region, UnusableMemoryMeta),
-            MemoryRegionType::Unknown => mark_ranges!(region, UnusableMemoryMeta),
+            MemoryRegionType::Unknown => {
+                // The holes only have metadata in the sections next to the present memory.
+                for section in vmemmap::present_sections() {
+                    let start = region.base().max(section.start);
+                    let end = region.end().min(section.end);
+                    if start < end {
+                        let part = MemoryRegion::new(start, end - start, MemoryRegionType::Unknown);
+                        mark_ranges!(part, UnusableMemoryMeta);
+                    }
+                }
+            }
             MemoryRegionType::NonVolatileSleep => mark_ranges!(region, UnusableMemoryMeta),
             MemoryRegionType::Reserved => mark_ranges!(region, UnusableMemoryMeta),
             MemoryRegionType::Kernel => mark_ranges!(region, KernelMeta),
@@ -33,6 +33,7 @@
 
 pub mod allocator;
 pub mod linked_list;
+mod memblock;
 pub mod meta;
 pub mod segment;
 pub mod unique;
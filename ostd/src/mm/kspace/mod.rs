@@ -13,10 +13,18 @@
 //! +-+ <- the highest used address (0xffff_ffff_ffff_0000)
 //! | |         For the kernel code, 1 GiB. Mapped frames are tracked.
 //! +-+ <- 0xffff_ffff_8000_0000
+//! | |         For the kernel extensions, 1 GiB. Mapped pages are tracked with handles.
+//! +-+ <- 0xffff_ffff_4000_0000
 //! | |
 //! | |         Unused hole.
+//! +-+ <- 0xffff_f800_0000_0000
+//! | |         For [`crate::mm::valloc`], 16 TiB. Mapped pages are tracked with handles.
+//! +-+ <- 0xffff_e800_0000_0000
+//! | |         Unused hole.
 //! +-+ <- 0xffff_e100_0000_0000
-//! | |         For frame metadata, 1 TiB. Mapped frames are untracked.
+//! | |         For the vmemmap of frame metadata, 1 TiB. Only the sections
+//! | |         with present physical memory are populated. Mapped frames are
+//! | |         untracked.
 //! +-+ <- 0xffff_e000_0000_0000
 //! | |         For [`KVirtArea<Tracked>`], 16 TiB. Mapped pages are tracked with handles.
 //! +-+ <- 0xffff_d000_0000_0000
@@ -35,9 +43,15 @@
 //!
 //! If the address width is (according to [`crate::arch::mm::PagingConsts`])
 //! 39 bits or 57 bits, the memory space just adjust proportionally.
+//!
+//! RISC-V uses the same layout with Sv48, except that the kernel code offset
+//! is `0xffff_ffff_0000_0000`, so the kernel image is still loaded above
+//! `0xffff_ffff_8000_0000`. The vmemmap covers 64 TiB of physical memory, whose
+//! sections are populated as described in [`super::frame::meta::vmemmap`].
 
 pub(crate) mod kvirt_area;
 
+use alloc::vec::Vec;
 use core::ops::Range;
 
 use align_ext::AlignExt;
@@ -48,7 +62,7 @@ mod test;
 
 use super::{
     frame::{
-        meta::{mapping, KernelMeta, MetaPageMeta},
+        meta::{mapping, vmemmap, KernelMeta, MetaPageMeta},
         Frame, Segment,
     },
     nr_subpage_per_huge,
@@ -86,6 +100,14 @@ const KERNEL_CODE_BASE_VADDR: usize = 0xffff_ffff_8000_0000 << ADDR_WIDTH_SHIFT;
 #[cfg(target_arch = "riscv64")]
 const KERNEL_CODE_BASE_VADDR: usize = 0xffff_ffff_0000_0000 << ADDR_WIDTH_SHIFT;
 
+const MODULES_CAP_VADDR: Vaddr = 0xffff_ffff_8000_0000 << ADDR_WIDTH_SHIFT;
+const MODULES_BASE_VADDR: Vaddr = 0xffff_ffff_4000_0000 << ADDR_WIDTH_SHIFT;
+pub(in crate::mm) const MODULES_VADDR_RANGE: Range<Vaddr> = MODULES_BASE_VADDR..MODULES_CAP_VADDR;
+
+const VALLOC_CAP_VADDR: Vaddr = 0xffff_f800_0000_0000 << ADDR_WIDTH_SHIFT;
+const VALLOC_BASE_VADDR: Vaddr = 0xffff_e800_0000_0000 << ADDR_WIDTH_SHIFT;
+pub(in crate::mm) const VALLOC_VADDR_RANGE: Range<Vaddr> = VALLOC_BASE_VADDR..VALLOC_CAP_VADDR;
+
 const FRAME_METADATA_CAP_VADDR: Vaddr = 0xffff_e100_0000_0000 << ADDR_WIDTH_SHIFT;
 const FRAME_METADATA_BASE_VADDR: Vaddr = 0xffff_e000_0000_0000 << ADDR_WIDTH_SHIFT;
 pub(in crate::mm) const FRAME_METADATA_RANGE: Range<Vaddr> =
@@ -148,33 +170,37 @@ pub fn init_kernel_page_table(meta_pages: Segment<MetaPageMeta>) {
 
     // Do linear mappings for the kernel.
     {
-        let from = LINEAR_MAPPING_BASE_VADDR..LINEAR_MAPPING_BASE_VADDR + phys_mem_cap;
-        let to = 0..phys_mem_cap;
         let prop = PageProperty {
             flags: PageFlags::RW,
             cache: CachePolicy::Writeback,
             priv_flags: PrivilegedPageFlags::GLOBAL,
         };
-        // SAFETY: we are doing the linear mapping for the kernel.
-        unsafe {
-            kpt.map(&from, &to, prop).unwrap();
+        for to in linear_mapping_ranges(phys_mem_cap) {
+            let from = LINEAR_MAPPING_BASE_VADDR + to.start..LINEAR_MAPPING_BASE_VADDR + to.end;
+            // SAFETY: we are doing the linear mapping for the kernel.
+            unsafe {
+                kpt.map(&from, &to, prop).unwrap();
+            }
         }
     }
 
-    // Map the metadata pages.
+    // Map the metadata pages to the vmemmap, where only the present sections are populated.
     {
-        let start_va = mapping::frame_to_meta::<PagingConsts>(0);
-        let from = start_va..start_va + meta_pages.size();
         let prop = PageProperty {
             flags: PageFlags::RW,
             cache: CachePolicy::Writeback,
             priv_flags: PrivilegedPageFlags::GLOBAL,
         };
-        let mut cursor = kpt.cursor_mut(&from).unwrap();
-        for meta_page in meta_pages {
-            // SAFETY: we are doing the metadata mappings for the kernel.
-            unsafe {
-                let _old = cursor.map(meta_page.into(), prop);
+        let mut meta_pages = meta_pages;
+        for section in vmemmap::present_sections() {
+            let start_va = mapping::frame_to_meta::<PagingConsts>(section.start);
+            let from = start_va..start_va + vmemmap::SECTION_META_SIZE;
+            let mut cursor = kpt.cursor_mut(&from).unwrap();
+            for meta_page in meta_pages.by_ref().take(from.len() / PAGE_SIZE) {
+                // SAFETY: we are doing the metadata mappings for the kernel.
+                unsafe {
+                    let _old = cursor.map(meta_page.into(), prop);
+                }
             }
         }
     }
@@ -226,6 +252,25 @@ pub fn init_kernel_page_table(meta_pages: Segment<MetaPageMeta>) {
     KERNEL_PAGE_TABLE.call_once(|| kpt);
 }
 
+/// Returns the physical memory ranges that are linearly mapped.
+///
+/// On RISC-V, the memory of the firmware is not mapped, since even the
+/// speculative accesses to it may fault.
+fn linear_mapping_ranges(phys_mem_cap: Paddr) -> Vec<Range<Paddr>> {
+    #[cfg(target_arch = "riscv64")]
+    let holes = crate::arch::mm::firmware::ranges();
+    #[cfg(not(target_arch = "riscv64"))]
+    let holes = core::iter::empty::<Range<Paddr>>();
+
+    let ranges = core::iter::once(0..phys_mem_cap).collect();
+    holes.fold(ranges, |ranges: Vec<_>, hole| {
+        ranges
+            .iter()
+            .flat_map(|range| crate::util::range_difference(range, &hole))
+            .collect()
+    })
+}
+
 /// Activates the kernel page table.
 ///
 /// # Safety
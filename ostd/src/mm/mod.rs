@@ -17,6 +17,7 @@ mod offset;
 pub(crate) mod page_prop;
 pub(crate) mod page_table;
 pub mod tlb;
+pub mod valloc;
 pub mod vm_space;
 
 use core::{fmt::Debug, ops::Range};
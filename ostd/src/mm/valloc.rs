@@ -0,0 +1,274 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Virtually contiguous kernel memory allocation.
+//!
+//! A [`VAlloc`] is a kernel buffer that is contiguous in the virtual memory
+//! but not in the physical memory, like what `vmalloc` of Linux allocates.
+//! The buffer is backed by frames that are allocated one by one and are mapped
+//! in a dedicated region of the kernel space ([`VALLOC_VADDR_RANGE`]), so that
+//! an arbitrarily large buffer can be allocated even if the physical memory is
+//! fragmented.
+//!
+//! Each buffer is surrounded by unmapped guard pages. So an overflow or an
+//! underflow of the buffer faults instead of corrupting its neighbours.
+//!
+//! The memory of the kernel extensions is also allocated here, but in the
+//! region close to the kernel code ([`MODULES_VADDR_RANGE`]), so that the
+//! extensions can call the kernel with PC-relative instructions.
+//!
+//! The buffers can only be accessed after the kernel page table is activated.
+
+use core::ops::Range;
+
+use super::{
+    kspace::{KERNEL_PAGE_TABLE, MODULES_VADDR_RANGE, VALLOC_VADDR_RANGE},
+    page_prop::{CachePolicy, PageFlags, PageProperty, PrivilegedPageFlags},
+    page_table::PageTableItem,
+    tlb::{TlbFlushOp, TlbFlusher},
+    FrameAllocOptions, Infallible, Vaddr, VmIo, VmReader, VmWriter, PAGE_SIZE,
+};
+use crate::{
+    arch::irq, cpu::CpuSet, prelude::*, task::disable_preempt, util::range_alloc::RangeAllocator,
+    Error,
+};
+
+/// The size of the guard pages on each side of a buffer.
+const GUARD_SIZE: usize = PAGE_SIZE;
+
+static VALLOC_ALLOCATOR: RangeAllocator = RangeAllocator::new(VALLOC_VADDR_RANGE);
+static MODULES_ALLOCATOR: RangeAllocator = RangeAllocator::new(MODULES_VADDR_RANGE);
+
+/// A virtually contiguous kernel buffer.
+///
+/// The buffer is readable and writable, and is zeroed when it is allocated.
+/// It is freed when dropped.
+///
+/// # Examples
+///
+/// ```
+/// use ostd::mm::{valloc::VAlloc, VmIo, PAGE_SIZE};
+///
+/// // A buffer of 64 MiB, which does not need 64 MiB of contiguous frames.
+/// let buf = VAlloc::new(64 * 1024 * 1024).unwrap();
+/// buf.write_val(PAGE_SIZE * 100, &42u64).unwrap();
+/// assert_eq!(buf.read_val::<u64>(PAGE_SIZE * 100).unwrap(), 42);
+/// ```
+#[derive(Debug)]
+pub struct VAlloc {
+    /// The virtual memory range, including the guard pages.
+    area: Range<Vaddr>,
+    allocator: &'static RangeAllocator,
+}
+
+impl VAlloc {
+    /// Allocates a buffer of at least `size` bytes.
+    ///
+    /// The size is rounded up to a multiple of [`PAGE_SIZE`].
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the size is zero.
+    pub fn new(size: usize) -> Result<Self> {
+        Self::new_in(&VALLOC_ALLOCATOR, size)
+    }
+
+    /// Allocates a buffer of at least `size` bytes for a kernel extension.
+    ///
+    /// The buffer is within 2 GiB from the kernel code.
+    pub(crate) fn new_for_module(size: usize) -> Result<Self> {
+        Self::new_in(&MODULES_ALLOCATOR, size)
+    }
+
+    fn new_in(allocator: &'static RangeAllocator, size: usize) -> Result<Self> {
+        assert!(size > 0);
+
+        let size = size
+            .checked_next_multiple_of(PAGE_SIZE)
+            .ok_or(Error::Overflow)?;
+        let area_size = size.checked_add(GUARD_SIZE * 2).ok_or(Error::Overflow)?;
+        // The allocated frames are unmapped and freed when `valloc` is dropped on failures.
+        let valloc = Self {
+            area: allocator.alloc(area_size)?,
+            allocator,
+        };
+
+        let prop = PageProperty {
+            flags: PageFlags::RW,
+            cache: CachePolicy::Writeback,
+            priv_flags: PrivilegedPageFlags::GLOBAL,
+        };
+        let page_table = KERNEL_PAGE_TABLE.get().unwrap();
+        let mut cursor = page_table.cursor_mut(&valloc.range()).unwrap();
+        for _ in 0..size / PAGE_SIZE {
+            let frame = FrameAllocOptions::new().alloc_frame()?;
+            // SAFETY: The range is allocated from the dedicated region, so the
+            // mapping does not affect kernel's memory safety.
+            let old = unsafe { cursor.map(frame.into(), prop) };
+            debug_assert!(old.is_none());
+        }
+        drop(cursor);
+
+        Ok(valloc)
+    }
+
+    /// Returns the size of the buffer in bytes.
+    pub fn size(&self) -> usize {
+        self.area.len() - GUARD_SIZE * 2
+    }
+
+    /// Returns the virtual memory range of the buffer, excluding the guard pages.
+    pub fn range(&self) -> Range<Vaddr> {
+        self.area.start + GUARD_SIZE..self.area.end - GUARD_SIZE
+    }
+
+    /// Returns a raw pointer to the start of the buffer.
+    ///
+    /// The buffer is valid for reads and writes of [`Self::size`] bytes while
+    /// the `VAlloc` is alive. But the raw pointer must not be used along with
+    /// the readers and the writers of the buffer to access the same bytes.
+    pub fn as_mut_ptr(&self) -> *mut u8 {
+        self.range().start as *mut u8
+    }
+
+    /// Changes the access permissions of the pages in the range of the buffer.
+    ///
+    /// The range is relative to the start of the buffer. Once the writable
+    /// permission is removed, the buffer must not be written anymore.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if
+    ///  - the range is not aligned to [`PAGE_SIZE`] or is out of the buffer;
+    ///  - the function is called with IRQs disabled.
+    pub(crate) fn protect(&self, range: Range<usize>, flags: PageFlags) {
+        assert!(range.start % PAGE_SIZE == 0 && range.end % PAGE_SIZE == 0);
+        assert!(range.start <= range.end && range.end <= self.size());
+        if range.is_empty() {
+            return;
+        }
+
+        let start = self.range().start;
+        let va_range = start + range.start..start + range.end;
+        let page_table = KERNEL_PAGE_TABLE.get().unwrap();
+        let mut cursor = page_table.cursor_mut(&va_range).unwrap();
+        let mut op = |prop: &mut PageProperty| prop.flags = flags;
+        // SAFETY: The pages are only accessed via `self`, whose users are
+        // responsible for the permissions.
+        while unsafe { cursor.protect_next(va_range.end - cursor.virt_addr(), &mut op) }.is_some() {
+        }
+        drop(cursor);
+
+        let mut flusher = TlbFlusher::new(CpuSet::new_full(), disable_preempt());
+        flusher.issue_tlb_flush(TlbFlushOp::Range(va_range));
+        flusher.dispatch_tlb_flush();
+        flusher.sync_tlb_flush();
+    }
+
+    /// Returns a reader to read the buffer.
+    pub fn reader(&self) -> VmReader<'_, Infallible> {
+        // SAFETY: The buffer is mapped to untyped frames during the lifetime of `self`.
+        unsafe { VmReader::from_kernel_space(self.as_mut_ptr(), self.size()) }
+    }
+
+    /// Returns a writer to write the buffer.
+    pub fn writer(&self) -> VmWriter<'_, Infallible> {
+        // SAFETY: The buffer is mapped to untyped frames during the lifetime of `self`.
+        unsafe { VmWriter::from_kernel_space(self.as_mut_ptr(), self.size()) }
+    }
+}
+
+impl VmIo for VAlloc {
+    fn read(&self, offset: usize, writer: &mut VmWriter) -> Result<()> {
+        let read_len = writer.avail().min(self.size().saturating_sub(offset));
+        // Do bound check with potential integer overflow in mind
+        let max_offset = offset.checked_add(read_len).ok_or(Error::Overflow)?;
+        if max_offset > self.size() {
+            return Err(Error::InvalidArgs);
+        }
+        let len = self
+            .reader()
+            .skip(offset)
+            .read_fallible(writer)
+            .map_err(|(e, _)| e)?;
+        debug_assert!(len == read_len);
+        Ok(())
+    }
+
+    fn write(&self, offset: usize, reader: &mut VmReader) -> Result<()> {
+        let write_len = reader.remain().min(self.size().saturating_sub(offset));
+        // Do bound check with potential integer overflow in mind
+        let max_offset = offset.checked_add(write_len).ok_or(Error::Overflow)?;
+        if max_offset > self.size() {
+            return Err(Error::InvalidArgs);
+        }
+        let len = self
+            .writer()
+            .skip(offset)
+            .write_fallible(reader)
+            .map_err(|(e, _)| e)?;
+        debug_assert!(len == write_len);
+        Ok(())
+    }
+}
+
+impl Drop for VAlloc {
+    fn drop(&mut self) {
+        let page_table = KERNEL_PAGE_TABLE.get().unwrap();
+        let mut cursor = page_table.cursor_mut(&self.area).unwrap();
+        let mut flusher = TlbFlusher::new(CpuSet::new_full(), disable_preempt());
+        loop {
+            // SAFETY: The pages are only accessed via `self`, which is being dropped.
+            let PageTableItem::Mapped { va, page, .. } =
+                (unsafe { cursor.take_next(self.area.end - cursor.virt_addr()) })
+            else {
+                break;
+            };
+            // The frames are freed after the TLB entries on all CPUs are flushed.
+            flusher.issue_tlb_flush_with(TlbFlushOp::Address(va), page);
+        }
+        drop(cursor);
+        flusher.dispatch_tlb_flush();
+
+        // Waiting for the remote flushes with IRQs disabled may deadlock. The
+        // frames are still freed after the flushes, but the virtual memory may
+        // be reused before that, which is harmless since the stale entries only
+        // point to the frames that have not yet been freed.
+        if irq::is_local_enabled() {
+            flusher.sync_tlb_flush();
+        }
+
+        self.allocator.free(self.area.clone());
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use super::*;
+    use crate::prelude::ktest;
+
+    #[ktest]
+    fn valloc_read_write() {
+        let buf = VAlloc::new(PAGE_SIZE * 3 + 1).unwrap();
+        assert_eq!(buf.size(), PAGE_SIZE * 4);
+        assert!(VALLOC_VADDR_RANGE.contains(&buf.range().start));
+
+        assert_eq!(buf.read_val::<u64>(PAGE_SIZE * 2).unwrap(), 0);
+        buf.write_val(PAGE_SIZE * 4 - 8, &0xdead_beef_u64).unwrap();
+        assert_eq!(buf.read_val::<u64>(PAGE_SIZE * 4 - 8).unwrap(), 0xdead_beef);
+        assert!(buf.write_val(PAGE_SIZE * 4 - 4, &0u64).is_err());
+    }
+
+    #[ktest]
+    fn valloc_guard_pages() {
+        let buf = VAlloc::new(PAGE_SIZE).unwrap();
+        let page_table = KERNEL_PAGE_TABLE.get().unwrap();
+        let is_mapped = |va: Vaddr| {
+            let mut cursor = page_table.cursor(&(va..va + PAGE_SIZE)).unwrap();
+            matches!(cursor.query().unwrap(), PageTableItem::Mapped { .. })
+        };
+
+        assert!(is_mapped(buf.range().start));
+        assert!(!is_mapped(buf.range().start - PAGE_SIZE));
+        assert!(!is_mapped(buf.range().end));
+    }
+}
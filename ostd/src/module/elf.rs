@@ -0,0 +1,252 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! A minimal parser of the relocatable ELF64 objects.
+
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use super::ModuleError;
+
+pub(super) const SHT_SYMTAB: u32 = 2;
+pub(super) const SHT_RELA: u32 = 4;
+pub(super) const SHT_NOBITS: u32 = 8;
+const SHT_REL: u32 = 9;
+
+pub(super) const SHF_WRITE: u64 = 0x1;
+pub(super) const SHF_ALLOC: u64 = 0x2;
+pub(super) const SHF_EXECINSTR: u64 = 0x4;
+
+pub(super) const SHN_UNDEF: u16 = 0;
+const SHN_LORESERVE: u16 = 0xff00;
+pub(super) const SHN_ABS: u16 = 0xfff1;
+pub(super) const SHN_COMMON: u16 = 0xfff2;
+
+pub(super) const STB_WEAK: u8 = 2;
+pub(super) const STT_OBJECT: u8 = 1;
+pub(super) const STT_FUNC: u8 = 2;
+
+const ET_REL: u16 = 1;
+const ELFCLASS64: u8 = 2;
+const ELFDATA2LSB: u8 = 1;
+
+const EHDR_SIZE: usize = 64;
+const SHDR_SIZE: usize = 64;
+const SYM_SIZE: usize = 24;
+const RELA_SIZE: usize = 24;
+
+/// A section header.
+#[derive(Debug, Clone)]
+pub(super) struct Section {
+    pub(super) typ: u32,
+    pub(super) flags: u64,
+    pub(super) offset: usize,
+    pub(super) size: usize,
+    pub(super) link: u32,
+    pub(super) info: u32,
+    pub(super) align: usize,
+}
+
+/// An entry of the symbol table.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Symbol {
+    pub(super) name: u32,
+    pub(super) info: u8,
+    pub(super) shndx: u16,
+    pub(super) value: u64,
+    pub(super) size: u64,
+}
+
+impl Symbol {
+    pub(super) fn bind(&self) -> u8 {
+        self.info >> 4
+    }
+
+    pub(super) fn typ(&self) -> u8 {
+        self.info & 0xf
+    }
+
+    /// Returns whether the symbol is defined in a section other than the special ones.
+    pub(super) fn is_in_section(&self) -> bool {
+        self.shndx != SHN_UNDEF && self.shndx < SHN_LORESERVE
+    }
+}
+
+/// An entry of a relocation table with addends.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Rela {
+    pub(super) offset: u64,
+    pub(super) typ: u32,
+    pub(super) sym: u32,
+    pub(super) addend: i64,
+}
+
+/// A relocatable ELF64 object.
+pub(super) struct Elf<'a> {
+    image: &'a [u8],
+    sections: Vec<Section>,
+    symtab: usize,
+}
+
+impl<'a> Elf<'a> {
+    /// Parses the object, which should be built for the machine.
+    pub(super) fn parse(image: &'a [u8], machine: u16) -> Result<Self, ModuleError> {
+        let ident = bytes(image, 0..16)?;
+        if ident[..4] != *b"\x7fELF" || ident[4] != ELFCLASS64 || ident[5] != ELFDATA2LSB {
+            return Err(ModuleError::InvalidImage(
+                "not a little-endian ELF64 object",
+            ));
+        }
+        if read_u16(image, 16)? != ET_REL {
+            return Err(ModuleError::InvalidImage("not a relocatable object"));
+        }
+        if read_u16(image, 18)? != machine {
+            return Err(ModuleError::InvalidImage("built for another machine"));
+        }
+
+        let shoff = read_u64(image, 40)? as usize;
+        let shentsize = read_u16(image, 58)? as usize;
+        let shnum = read_u16(image, 60)? as usize;
+        if shentsize != SHDR_SIZE || shoff < EHDR_SIZE {
+            return Err(ModuleError::InvalidImage("bad section headers"));
+        }
+
+        let mut sections = Vec::with_capacity(shnum);
+        for index in 0..shnum {
+            let base = index
+                .checked_mul(SHDR_SIZE)
+                .and_then(|offset| offset.checked_add(shoff))
+                .ok_or(ModuleError::InvalidImage("bad section headers"))?;
+            let section = Section {
+                typ: read_u32(image, base + 4)?,
+                flags: read_u64(image, base + 8)?,
+                offset: read_u64(image, base + 24)? as usize,
+                size: read_u64(image, base + 32)? as usize,
+                link: read_u32(image, base + 40)?,
+                info: read_u32(image, base + 44)?,
+                align: (read_u64(image, base + 48)? as usize).max(1),
+            };
+            if section.typ == SHT_REL {
+                return Err(ModuleError::InvalidImage("relocations without addends"));
+            }
+            if !section.align.is_power_of_two() {
+                return Err(ModuleError::InvalidImage("bad section alignment"));
+            }
+            if section.typ != SHT_NOBITS {
+                bytes(
+                    image,
+                    section.offset..section.offset.wrapping_add(section.size),
+                )?;
+            }
+            sections.push(section);
+        }
+
+        let symtab = sections
+            .iter()
+            .position(|section| section.typ == SHT_SYMTAB)
+            .ok_or(ModuleError::InvalidImage("no symbol table"))?;
+        if sections[symtab].link as usize >= sections.len() {
+            return Err(ModuleError::InvalidImage("bad string table"));
+        }
+
+        Ok(Self {
+            image,
+            sections,
+            symtab,
+        })
+    }
+
+    pub(super) fn sections(&self) -> &[Section] {
+        &self.sections
+    }
+
+    /// Returns the content of the section, which is empty for `SHT_NOBITS`.
+    pub(super) fn section_data(&self, section: &Section) -> &'a [u8] {
+        if section.typ == SHT_NOBITS {
+            return &[];
+        }
+        // The range is checked when parsing.
+        &self.image[section.offset..section.offset + section.size]
+    }
+
+    pub(super) fn nr_symbols(&self) -> usize {
+        self.sections[self.symtab].size / SYM_SIZE
+    }
+
+    pub(super) fn symbol(&self, index: usize) -> Result<Symbol, ModuleError> {
+        if index >= self.nr_symbols() {
+            return Err(ModuleError::InvalidImage("bad symbol index"));
+        }
+        let data = self.section_data(&self.sections[self.symtab]);
+        let base = index * SYM_SIZE;
+        Ok(Symbol {
+            name: read_u32(data, base)?,
+            info: read_u8(data, base + 4)?,
+            shndx: read_u16(data, base + 6)?,
+            value: read_u64(data, base + 8)?,
+            size: read_u64(data, base + 16)?,
+        })
+    }
+
+    pub(super) fn symbol_name(&self, symbol: &Symbol) -> Result<&'a str, ModuleError> {
+        let strtab = &self.sections[self.sections[self.symtab].link as usize];
+        string(self.section_data(strtab), symbol.name as usize)
+    }
+
+    /// Returns the relocations in the `SHT_RELA` section.
+    pub(super) fn relas(
+        &self,
+        section: &Section,
+    ) -> impl Iterator<Item = Result<Rela, ModuleError>> + 'a {
+        let data = self.section_data(section);
+        (0..data.len() / RELA_SIZE).map(move |index| {
+            let base = index * RELA_SIZE;
+            let info = read_u64(data, base + 8)?;
+            Ok(Rela {
+                offset: read_u64(data, base)?,
+                typ: info as u32,
+                sym: (info >> 32) as u32,
+                addend: read_u64(data, base + 16)? as i64,
+            })
+        })
+    }
+}
+
+fn bytes(image: &[u8], range: Range<usize>) -> Result<&[u8], ModuleError> {
+    image
+        .get(range)
+        .ok_or(ModuleError::InvalidImage("truncated image"))
+}
+
+fn read_array<const N: usize>(image: &[u8], offset: usize) -> Result<[u8; N], ModuleError> {
+    let end = offset
+        .checked_add(N)
+        .ok_or(ModuleError::InvalidImage("truncated image"))?;
+    Ok(bytes(image, offset..end)?.try_into().unwrap())
+}
+
+fn read_u8(image: &[u8], offset: usize) -> Result<u8, ModuleError> {
+    Ok(read_array::<1>(image, offset)?[0])
+}
+
+fn read_u16(image: &[u8], offset: usize) -> Result<u16, ModuleError> {
+    read_array(image, offset).map(u16::from_le_bytes)
+}
+
+fn read_u32(image: &[u8], offset: usize) -> Result<u32, ModuleError> {
+    read_array(image, offset).map(u32::from_le_bytes)
+}
+
+fn read_u64(image: &[u8], offset: usize) -> Result<u64, ModuleError> {
+    read_array(image, offset).map(u64::from_le_bytes)
+}
+
+fn string(table: &[u8], offset: usize) -> Result<&str, ModuleError> {
+    let bytes = table
+        .get(offset..)
+        .ok_or(ModuleError::InvalidImage("bad string offset"))?;
+    let len = bytes
+        .iter()
+        .position(|byte| *byte == 0)
+        .ok_or(ModuleError::InvalidImage("unterminated string"))?;
+    core::str::from_utf8(&bytes[..len]).map_err(|_| ModuleError::InvalidImage("bad string"))
+}
@@ -0,0 +1,150 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The table of the kernel symbols exported to the kernel extensions (ksymtab).
+//!
+//! A kernel extension can only refer to the kernel symbols in the table, which
+//! are exported with [`export_symbol!`] and are placed in the `.ksymtab`
+//! section by the linker. The extensions refer to them by the exported names,
+//! so they are usually declared in an `extern "C"` block in the extensions.
+//!
+//! [`export_symbol!`]: crate::export_symbol
+
+use alloc::alloc::Layout;
+use core::mem::size_of;
+
+use crate::mm::Vaddr;
+
+/// A kernel symbol exported to the kernel extensions.
+#[repr(C)]
+pub struct KernelSymbol {
+    name: &'static str,
+    addr: *const (),
+}
+
+// SAFETY: The symbol is immutable and its address is never dereferenced here.
+unsafe impl Sync for KernelSymbol {}
+
+impl KernelSymbol {
+    /// Creates a kernel symbol. Use [`export_symbol!`] instead.
+    ///
+    /// [`export_symbol!`]: crate::export_symbol
+    #[doc(hidden)]
+    pub const fn new(name: &'static str, addr: *const ()) -> Self {
+        Self { name, addr }
+    }
+
+    /// Returns the exported name of the symbol.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the address of the symbol.
+    pub fn addr(&self) -> Vaddr {
+        self.addr as Vaddr
+    }
+}
+
+/// Exports a kernel function or static to the kernel extensions.
+///
+/// The symbol is exported with its name. The statics are exported with the
+/// `static` keyword.
+///
+/// # Examples
+///
+/// ```
+/// use core::sync::atomic::AtomicUsize;
+///
+/// pub extern "C" fn get_answer() -> usize {
+///     42
+/// }
+/// ostd::export_symbol!(get_answer);
+///
+/// pub static NR_ANSWERS: AtomicUsize = AtomicUsize::new(0);
+/// ostd::export_symbol!(static NR_ANSWERS);
+/// ```
+#[macro_export]
+macro_rules! export_symbol {
+    (static $name:ident) => {
+        $crate::export_symbol!(@entry $name, core::ptr::addr_of!($name).cast::<()>());
+    };
+    ($name:ident) => {
+        $crate::export_symbol!(@entry $name, $name as *const ());
+    };
+    (@entry $name:ident, $addr:expr) => {
+        const _: () = {
+            #[used]
+            #[link_section = ".ksymtab"]
+            static KSYMTAB_ENTRY: $crate::module::KernelSymbol =
+                $crate::module::KernelSymbol::new(stringify!($name), $addr);
+        };
+    };
+}
+
+/// Returns all the exported kernel symbols.
+pub fn kernel_symbols() -> &'static [KernelSymbol] {
+    extern "C" {
+        fn __ksymtab();
+        fn __ksymtab_end();
+    }
+
+    let start = __ksymtab as usize;
+    let len = (__ksymtab_end as usize - start) / size_of::<KernelSymbol>();
+    // SAFETY: The `.ksymtab` section only contains the entries defined by
+    // `export_symbol!`, which are immutable.
+    unsafe { core::slice::from_raw_parts(start as *const KernelSymbol, len) }
+}
+
+/// Looks up the address of the exported kernel symbol by its name.
+pub(super) fn lookup(name: &str) -> Option<Vaddr> {
+    kernel_symbols()
+        .iter()
+        .find(|symbol| symbol.name == name)
+        .map(KernelSymbol::addr)
+}
+
+// The basic services for the kernel extensions, besides the symbols exported by the kernel.
+
+/// Logs a message of a kernel extension with the level, which ranges from 1
+/// (error) to 5 (trace) as [`log::Level`] does.
+///
+/// # Safety
+///
+/// The message must be valid for reads of `len` bytes.
+unsafe extern "C" fn ostd_log(level: usize, msg: *const u8, len: usize) {
+    // SAFETY: The caller guarantees that the message is valid.
+    let bytes = unsafe { core::slice::from_raw_parts(msg, len) };
+    let level = match level {
+        1 => log::Level::Error,
+        2 => log::Level::Warn,
+        3 => log::Level::Info,
+        4 => log::Level::Debug,
+        _ => log::Level::Trace,
+    };
+    log::log!(
+        level,
+        "{}",
+        core::str::from_utf8(bytes).unwrap_or("<invalid UTF-8>")
+    );
+}
+export_symbol!(ostd_log);
+
+/// Allocates memory from the kernel heap, or returns null on failures.
+extern "C" fn ostd_alloc(size: usize, align: usize) -> *mut u8 {
+    match Layout::from_size_align(size, align) {
+        // SAFETY: The layout has a non-zero size.
+        Ok(layout) if layout.size() > 0 => unsafe { alloc::alloc::alloc(layout) },
+        _ => core::ptr::null_mut(),
+    }
+}
+export_symbol!(ostd_alloc);
+
+/// Frees the memory allocated by `ostd_alloc`.
+///
+/// # Safety
+///
+/// The memory must be allocated by `ostd_alloc` with the same size and alignment.
+unsafe extern "C" fn ostd_free(ptr: *mut u8, size: usize, align: usize) {
+    // SAFETY: The caller guarantees that the memory is allocated with the layout.
+    unsafe { alloc::alloc::dealloc(ptr, Layout::from_size_align_unchecked(size, align)) };
+}
+export_symbol!(ostd_free);
@@ -0,0 +1,426 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Loadable kernel extensions.
+//!
+//! A kernel extension (e.g., a driver or a file system) is a relocatable ELF
+//! object (`ET_REL`) that is loaded into the kernel at runtime. The extension
+//! can only refer to the kernel symbols exported with [`export_symbol!`],
+//! which are resolved when the extension is loaded.
+//!
+//! The extension may define two hooks with the C ABI:
+//!  - `init_module`, of type `extern "C" fn() -> i32`, is called after the
+//!    extension is loaded. The extension is not loaded if it returns a
+//!    nonzero value;
+//!  - `cleanup_module`, of type `extern "C" fn()`, is called before the
+//!    extension is unloaded. The extension cannot be unloaded without it.
+//!
+//! The sections of an extension are loaded into three groups of pages: the
+//! code that is readable and executable, the read-only data that is readable,
+//! and the data that is readable and writable. So no page of an extension is
+//! both writable and executable once it is loaded.
+//!
+//! [`export_symbol!`]: crate::export_symbol
+
+mod elf;
+mod ksymtab;
+
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+use core::{mem::size_of, ops::Range};
+
+use align_ext::AlignExt;
+use elf::{Elf, Section};
+pub use ksymtab::{kernel_symbols, KernelSymbol};
+
+use crate::{
+    arch::{self, module::ELF_MACHINE},
+    mm::{page_prop::PageFlags, valloc::VAlloc, Vaddr, PAGE_SIZE},
+    sync::Mutex,
+};
+
+/// An error that occurs when loading or unloading a kernel extension.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModuleError {
+    /// The image is not a valid relocatable object for the machine.
+    InvalidImage(&'static str),
+    /// A relocation of the type is not supported.
+    UnsupportedRelocation(u32),
+    /// The value of a relocation of the type does not fit in its place.
+    RelocationOverflow(u32),
+    /// The symbol is neither defined nor exported by the kernel.
+    UndefinedSymbol(String),
+    /// An extension of the same name has been loaded.
+    AlreadyLoaded,
+    /// No extension of the name has been loaded.
+    NotLoaded,
+    /// The extension has no `cleanup_module` hook.
+    NotUnloadable,
+    /// The `init_module` hook returns the error code.
+    InitFailed(i32),
+    /// There is not enough memory for the extension.
+    NoMemory,
+}
+
+impl From<crate::Error> for ModuleError {
+    fn from(_: crate::Error) -> Self {
+        Self::NoMemory
+    }
+}
+
+/// A relocation to be applied by the architecture.
+pub(crate) struct Relocation {
+    /// The architecture-specific type.
+    pub(crate) typ: u32,
+    /// The address to be relocated.
+    pub(crate) place: Vaddr,
+    /// The end address of the section of the place.
+    pub(crate) section_end: Vaddr,
+    /// The address of the symbol.
+    pub(crate) sym_value: u64,
+    pub(crate) addend: i64,
+    /// The address of the GOT entry of the symbol, if the architecture
+    /// requires one for the type.
+    pub(crate) got_entry: Option<Vaddr>,
+}
+
+impl Relocation {
+    /// Returns the symbol address plus the addend (`S + A`).
+    pub(crate) fn value(&self) -> u64 {
+        self.sym_value.wrapping_add(self.addend as u64)
+    }
+
+    /// Returns the value relative to the place (`S + A - P`).
+    pub(crate) fn pcrel(&self) -> i64 {
+        self.value().wrapping_sub(self.place as u64) as i64
+    }
+}
+
+/// A loaded kernel extension.
+#[derive(Debug)]
+pub struct Module {
+    name: String,
+    memory: VAlloc,
+    exit: Option<extern "C" fn()>,
+    symbols: Vec<ModuleSymbol>,
+}
+
+/// A function or an object defined in a kernel extension.
+#[derive(Debug, Clone)]
+pub struct ModuleSymbol {
+    /// The name of the symbol.
+    pub name: String,
+    /// The address of the symbol.
+    pub addr: Vaddr,
+    /// The size of the symbol in bytes.
+    pub size: usize,
+}
+
+impl Module {
+    /// Returns the name of the extension.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the virtual memory range where the extension is loaded.
+    pub fn range(&self) -> Range<Vaddr> {
+        self.memory.range()
+    }
+
+    /// Returns the functions and the objects defined in the extension.
+    pub fn symbols(&self) -> &[ModuleSymbol] {
+        &self.symbols
+    }
+}
+
+static MODULES: Mutex<Vec<Arc<Module>>> = Mutex::new(Vec::new());
+
+/// The size of a GOT entry.
+const GOT_ENTRY_SIZE: usize = size_of::<u64>();
+
+/// The names of the hooks.
+const INIT_HOOK: &str = "init_module";
+const EXIT_HOOK: &str = "cleanup_module";
+
+/// Loads the kernel extension in the image with the name and calls its
+/// `init_module` hook.
+///
+/// # Panics
+///
+/// This function panics if it is called with IRQs disabled.
+pub fn load(name: &str, image: &[u8]) -> Result<Arc<Module>, ModuleError> {
+    // The lock is held during the loading so that no extension of the same
+    // name can be loaded simultaneously.
+    let mut modules = MODULES.lock();
+    if modules.iter().any(|module| module.name == name) {
+        return Err(ModuleError::AlreadyLoaded);
+    }
+
+    let elf = Elf::parse(image, ELF_MACHINE)?;
+    let layout = Layout::new(&elf)?;
+    let memory = VAlloc::new_for_module(layout.size.max(PAGE_SIZE))?;
+    let base = memory.range().start;
+
+    for (index, section) in elf.sections().iter().enumerate() {
+        let Some(offset) = layout.offsets[index] else {
+            continue;
+        };
+        let data = elf.section_data(section);
+        // SAFETY: The section is within the memory, which is writable now.
+        unsafe {
+            core::ptr::copy_nonoverlapping(data.as_ptr(), (base + offset) as *mut u8, data.len())
+        };
+    }
+
+    let section_addr = |index: usize| {
+        layout
+            .offsets
+            .get(index)
+            .copied()
+            .flatten()
+            .map(|offset| base + offset)
+    };
+
+    // Resolve the symbols.
+    let mut symbol_values = vec![None; elf.nr_symbols()];
+    if let Some(null) = symbol_values.first_mut() {
+        *null = Some(0);
+    }
+    let mut symbols = Vec::new();
+    for (index, value) in symbol_values.iter_mut().enumerate().skip(1) {
+        let symbol = elf.symbol(index)?;
+        *value = match symbol.shndx {
+            elf::SHN_UNDEF => {
+                let name = elf.symbol_name(&symbol)?;
+                match ksymtab::lookup(name) {
+                    Some(addr) => Some(addr as u64),
+                    None if symbol.bind() == elf::STB_WEAK => Some(0),
+                    None => return Err(ModuleError::UndefinedSymbol(name.to_string())),
+                }
+            }
+            elf::SHN_ABS => Some(symbol.value),
+            elf::SHN_COMMON => {
+                return Err(ModuleError::InvalidImage(
+                    "common symbols (build with -fno-common)",
+                ))
+            }
+            _ if symbol.is_in_section() => section_addr(symbol.shndx as usize)
+                .map(|addr| (addr as u64).wrapping_add(symbol.value)),
+            _ => return Err(ModuleError::InvalidImage("bad symbol section")),
+        };
+
+        let is_named = matches!(symbol.typ(), elf::STT_FUNC | elf::STT_OBJECT);
+        if let (true, Some(addr)) = (is_named && symbol.is_in_section(), *value) {
+            symbols.push(ModuleSymbol {
+                name: elf.symbol_name(&symbol)?.to_string(),
+                addr: addr as Vaddr,
+                size: symbol.size as usize,
+            });
+        }
+    }
+
+    // Fill the GOT.
+    for (&index, &offset) in layout.got_entries.iter() {
+        let value = symbol_values[index].ok_or(ModuleError::InvalidImage("bad GOT symbol"))?;
+        // SAFETY: The GOT entry is within the memory, which is writable now.
+        unsafe { ((base + offset) as *mut u64).write(value) };
+    }
+
+    // Relocate the loaded sections.
+    let mut relocs = Vec::new();
+    for section in elf.sections().iter().filter(|s| s.typ == elf::SHT_RELA) {
+        let target = section.info as usize;
+        let Some(target_addr) = section_addr(target) else {
+            continue;
+        };
+        let target_size = elf.sections()[target].size;
+        for rela in elf.relas(section) {
+            let rela = rela?;
+            if rela.offset as usize >= target_size {
+                return Err(ModuleError::InvalidImage("relocation out of the section"));
+            }
+            let sym_value = symbol_values
+                .get(rela.sym as usize)
+                .ok_or(ModuleError::InvalidImage("bad symbol index"))?
+                .ok_or(ModuleError::InvalidImage("symbol in an unloaded section"))?;
+            relocs.push(Relocation {
+                typ: rela.typ,
+                place: target_addr + rela.offset as usize,
+                section_end: target_addr + target_size,
+                sym_value,
+                addend: rela.addend,
+                got_entry: layout
+                    .got_entries
+                    .get(&(rela.sym as usize))
+                    .map(|offset| base + offset),
+            });
+        }
+        // SAFETY: The places are within the target section, which is writable now.
+        unsafe { arch::module::apply_relocations(&relocs)? };
+        relocs.clear();
+    }
+
+    // Enforce W^X.
+    memory.protect(layout.text.clone(), PageFlags::RX);
+    memory.protect(layout.rodata.clone(), PageFlags::R);
+    arch::mm::flush_icache_all();
+
+    let hook = |name: &str| {
+        symbols
+            .iter()
+            .find(|symbol| symbol.name == name)
+            .map(|symbol| symbol.addr)
+    };
+    let init = hook(INIT_HOOK);
+    let exit = hook(EXIT_HOOK);
+    let module = Arc::new(Module {
+        name: name.to_string(),
+        memory,
+        // SAFETY: The extension defines the hook with the signature.
+        exit: exit.map(|addr| unsafe { core::mem::transmute::<Vaddr, extern "C" fn()>(addr) }),
+        symbols,
+    });
+
+    if let Some(init) = init {
+        // SAFETY: The extension defines the hook with the signature.
+        let init = unsafe { core::mem::transmute::<Vaddr, extern "C" fn() -> i32>(init) };
+        let ret = init();
+        if ret != 0 {
+            return Err(ModuleError::InitFailed(ret));
+        }
+    }
+
+    log::info!("Loaded kernel extension {} at {:#x?}", name, module.range());
+    modules.push(module.clone());
+    Ok(module)
+}
+
+/// Calls the `cleanup_module` hook of the kernel extension with the name and
+/// unloads it.
+///
+/// The memory of the extension is freed once the returned [`Module`]s of the
+/// extension are all dropped. They must not refer to the extension anymore.
+pub fn unload(name: &str) -> Result<(), ModuleError> {
+    let mut modules = MODULES.lock();
+    let index = modules
+        .iter()
+        .position(|module| module.name == name)
+        .ok_or(ModuleError::NotLoaded)?;
+    let exit = modules[index].exit.ok_or(ModuleError::NotUnloadable)?;
+
+    exit();
+    modules.remove(index);
+    log::info!("Unloaded kernel extension {}", name);
+    Ok(())
+}
+
+/// Returns the loaded kernel extensions.
+pub fn modules() -> Vec<Arc<Module>> {
+    MODULES.lock().clone()
+}
+
+/// Finds the loaded kernel extension that contains the address without sleeping.
+///
+/// Returns `None` if the extensions are being loaded or unloaded.
+pub(crate) fn try_find_module(addr: Vaddr) -> Option<Arc<Module>> {
+    let modules = MODULES.try_lock()?;
+    modules
+        .iter()
+        .find(|module| module.range().contains(&addr))
+        .cloned()
+}
+
+/// The layout of the loaded sections, whose offsets are relative to the
+/// start of the memory of the extension.
+struct Layout {
+    /// The offsets of the sections, or `None` if not loaded.
+    offsets: Vec<Option<usize>>,
+    /// The offsets of the GOT entries of the symbols.
+    got_entries: BTreeMap<usize, usize>,
+    text: Range<usize>,
+    rodata: Range<usize>,
+    size: usize,
+}
+
+impl Layout {
+    fn new(elf: &Elf) -> Result<Self, ModuleError> {
+        let sections = elf.sections();
+        let mut offsets = vec![None; sections.len()];
+
+        let mut offset = place_sections(sections, &mut offsets, 0, |section| {
+            section.flags & elf::SHF_EXECINSTR != 0
+        })?;
+        let text = 0..offset.align_up(PAGE_SIZE);
+
+        offset = place_sections(sections, &mut offsets, text.end, |section| {
+            section.flags & (elf::SHF_EXECINSTR | elf::SHF_WRITE) == 0
+        })?;
+
+        // The GOT is read-only since it is filled before the pages are protected.
+        let mut got_entries = BTreeMap::new();
+        offset = offset.align_up(GOT_ENTRY_SIZE);
+        for section in sections.iter().filter(|s| s.typ == elf::SHT_RELA) {
+            if !sections.get(section.info as usize).is_some_and(is_loaded) {
+                continue;
+            }
+            for rela in elf.relas(section) {
+                let rela = rela?;
+                if arch::module::needs_got_entry(rela.typ) {
+                    got_entries.entry(rela.sym as usize).or_insert_with(|| {
+                        offset += GOT_ENTRY_SIZE;
+                        offset - GOT_ENTRY_SIZE
+                    });
+                }
+            }
+        }
+        let rodata = text.end..offset.align_up(PAGE_SIZE);
+
+        offset = place_sections(sections, &mut offsets, rodata.end, |section| {
+            section.flags & elf::SHF_WRITE != 0
+        })?;
+        let size = offset.align_up(PAGE_SIZE);
+
+        Ok(Self {
+            offsets,
+            got_entries,
+            text,
+            rodata,
+            size,
+        })
+    }
+}
+
+fn is_loaded(section: &Section) -> bool {
+    section.flags & elf::SHF_ALLOC != 0 && section.size > 0
+}
+
+/// Places the loaded sections that match the filter from the offset, and
+/// returns the end offset.
+fn place_sections(
+    sections: &[Section],
+    offsets: &mut [Option<usize>],
+    mut offset: usize,
+    filter: impl Fn(&Section) -> bool,
+) -> Result<usize, ModuleError> {
+    for (index, section) in sections.iter().enumerate() {
+        if !is_loaded(section) || !filter(section) {
+            continue;
+        }
+        if section.flags & elf::SHF_WRITE != 0 && section.flags & elf::SHF_EXECINSTR != 0 {
+            return Err(ModuleError::InvalidImage("writable and executable section"));
+        }
+        if section.align > PAGE_SIZE {
+            return Err(ModuleError::InvalidImage("section alignment too large"));
+        }
+        let start = offset.align_up(section.align);
+        offsets[index] = Some(start);
+        offset = start
+            .checked_add(section.size)
+            .ok_or(ModuleError::InvalidImage("section too large"))?;
+    }
+    Ok(offset)
+}
@@ -1,13 +1,29 @@
 // SPDX-License-Identifier: MPL-2.0
 
 //! Panic support.
+//!
+//! When the kernel panics, the panicking CPU enters the panic state with [`enter_panic`],
+//! which stops the other CPUs by inter-processor calls, so that the panic screen (the message,
+//! the registers, and the stack trace) is printed without being interleaved with the outputs
+//! of other CPUs, including those that panic concurrently. After printing, [`abort`] exits
+//! QEMU, halts, or restarts the system according to the `panic=` option of the kernel command
+//! line (see [`PanicPolicy`]).
 
-use core::ffi::c_void;
+use core::{
+    ffi::c_void,
+    fmt,
+    sync::atomic::{AtomicU32, AtomicUsize, Ordering},
+};
 
 pub use unwinding::panic::{begin_panic, catch_unwind};
 
 use crate::{
-    arch::qemu::{exit_qemu, QemuExitCode},
+    arch::{
+        qemu::{exit_qemu, QemuExitCode},
+        read_tsc, tsc_freq,
+    },
+    boot::EARLY_INFO,
+    cpu::{current_cpu_racy, num_cpus, CpuSet},
     early_print, early_println,
     sync::SpinLock,
 };
@@ -30,26 +46,230 @@ use unwinding::abi::{
 pub fn __ostd_panic_handler(info: &core::panic::PanicInfo) -> ! {
     let _irq_guard = crate::trap::disable_local();
 
-    crate::cpu_local_cell! {
-        static IN_PANIC: bool = false;
-    }
-
-    if IN_PANIC.load() {
+    if !enter_panic() {
         early_println!("The panic handler panicked {:#?}", info);
         abort();
     }
 
-    IN_PANIC.store(true);
-
-    early_println!("Non-resettable panic! {:#?}", info);
+    print_banner(format_args!(
+        "Non-resettable panic on CPU {}",
+        current_cpu_racy().as_usize()
+    ));
+    early_println!("{:#?}", info);
 
+    print_registers();
     print_stack_trace();
+    print_banner(format_args!("end of panic"));
     abort();
 }
 
-/// Aborts the QEMU
+/// The ID of the CPU in the panic state, or [`NO_CPU`] if the kernel is not panicking.
+static PANICKING_CPU: AtomicU32 = AtomicU32::new(NO_CPU);
+
+const NO_CPU: u32 = u32::MAX;
+
+/// The number of the CPUs that have been stopped since the kernel panics.
+static NR_STOPPED_CPUS: AtomicUsize = AtomicUsize::new(0);
+
+/// Puts the current CPU into the panic state and stops the other CPUs.
+///
+/// Only one CPU can be in the panic state. If another CPU is already in it, the current CPU
+/// is stopped instead, so the outputs of concurrent panics are never interleaved. The local
+/// IRQs should be disabled before calling this function.
+///
+/// This function returns `false` if the current CPU is already in the panic state, i.e., the
+/// panic handler itself panics.
+pub fn enter_panic() -> bool {
+    let cpu = current_cpu_racy().as_usize() as u32;
+    match PANICKING_CPU.compare_exchange(NO_CPU, cpu, Ordering::AcqRel, Ordering::Acquire) {
+        Ok(_) => {
+            stop_other_cpus();
+            true
+        }
+        Err(panicking_cpu) if panicking_cpu == cpu => false,
+        Err(_) => stop_this_cpu(),
+    }
+}
+
+/// Returns whether the kernel is panicking.
+///
+/// The stopped CPUs may hold locks forever, so the code that runs in the panic handler should
+/// not spin on locks if this function returns `true`.
+pub fn is_panicking() -> bool {
+    PANICKING_CPU.load(Ordering::Relaxed) != NO_CPU
+}
+
+fn stop_other_cpus() {
+    let nr_other_cpus = num_cpus() - 1;
+    if nr_other_cpus == 0 || !crate::smp::is_initialized() {
+        return;
+    }
+
+    fn stop_on_ipi() {
+        stop_this_cpu();
+    }
+
+    let mut targets = CpuSet::new_full();
+    targets.remove(current_cpu_racy());
+    crate::smp::inter_processor_call(&targets, stop_on_ipi);
+
+    // The CPUs that run with the local IRQs disabled cannot be stopped, so they are waited
+    // for one second at most.
+    let deadline = read_tsc().saturating_add(tsc_freq());
+    while NR_STOPPED_CPUS.load(Ordering::Acquire) < nr_other_cpus && read_tsc() < deadline {
+        core::hint::spin_loop();
+    }
+
+    let nr_stopped_cpus = NR_STOPPED_CPUS.load(Ordering::Acquire);
+    if nr_stopped_cpus < nr_other_cpus {
+        early_println!(
+            "{} of {} other CPUs are not stopped",
+            nr_other_cpus - nr_stopped_cpus,
+            nr_other_cpus
+        );
+    }
+}
+
+fn stop_this_cpu() -> ! {
+    crate::arch::irq::disable_local();
+    NR_STOPPED_CPUS.fetch_add(1, Ordering::Release);
+    loop {
+        core::hint::spin_loop();
+    }
+}
+
+/// What the kernel does after printing the panic.
+///
+/// It is specified by the `panic=` option of the kernel command line, which accepts `exit`,
+/// `halt`, `reboot`, or a number of seconds as in Linux: zero halts, a positive number restarts
+/// after the delay, and a negative number restarts immediately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PanicPolicy {
+    /// Exits QEMU with the failure code, which is the default.
+    Exit,
+    /// Stops the current CPU, which keeps the panic screen for inspection.
+    Halt,
+    /// Restarts the system after the delay in seconds.
+    ///
+    /// QEMU is exited instead if restarting is not supported on the platform.
+    Reboot(u64),
+}
+
+impl PanicPolicy {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "exit" => Some(Self::Exit),
+            "halt" => Some(Self::Halt),
+            "reboot" => Some(Self::Reboot(0)),
+            _ => match value.parse::<i64>().ok()? {
+                0 => Some(Self::Halt),
+                secs => Some(Self::Reboot(secs.max(0) as u64)),
+            },
+        }
+    }
+}
+
+/// Returns the panic policy specified by the kernel command line.
+pub fn panic_policy() -> PanicPolicy {
+    let Some(early_info) = EARLY_INFO.get() else {
+        return PanicPolicy::Exit;
+    };
+    early_info
+        .kernel_cmdline
+        .split(' ')
+        .filter_map(|arg| arg.strip_prefix("panic="))
+        .next_back()
+        .and_then(PanicPolicy::parse)
+        .unwrap_or(PanicPolicy::Exit)
+}
+
+/// Aborts the kernel according to the panic policy.
 pub fn abort() -> ! {
-    exit_qemu(QemuExitCode::Failed);
+    match panic_policy() {
+        PanicPolicy::Exit => exit_qemu(QemuExitCode::Failed),
+        PanicPolicy::Halt => {
+            early_println!("The system is halted.");
+            stop_this_cpu();
+        }
+        PanicPolicy::Reboot(delay_secs) => {
+            if delay_secs > 0 {
+                early_println!("Restarting the system in {} seconds.", delay_secs);
+                let delay = tsc_freq().saturating_mul(delay_secs);
+                let deadline = read_tsc().saturating_add(delay);
+                while read_tsc() < deadline {
+                    core::hint::spin_loop();
+                }
+            }
+            cfg_if::cfg_if! {
+                if #[cfg(target_arch = "riscv64")] {
+                    crate::arch::reboot();
+                } else {
+                    exit_qemu(QemuExitCode::Failed);
+                }
+            }
+        }
+    }
+}
+
+/// Prints a banner line of the panic screen, which is highlighted in bold red.
+pub fn print_banner(title: fmt::Arguments) {
+    early_println!("\x1b[1;31m---[ {} ]---\x1b[0m", title);
+}
+
+/// Prints all the general-purpose registers of the caller to the console.
+///
+/// The registers are recovered by unwinding the stack, so the caller-saved registers may have
+/// been clobbered by the callees and are only shown for reference.
+pub fn print_registers() {
+    cfg_if::cfg_if! {
+        if #[cfg(target_arch = "x86_64")] {
+            // RAX-R15 and the return address.
+            const NR_REGS: u16 = 17;
+        } else if #[cfg(target_arch = "riscv64")] {
+            const NR_REGS: u16 = 32;
+        } else {
+            const NR_REGS: u16 = 8;
+        }
+    }
+
+    extern "C" fn callback(unwind_ctx: &UnwindContext<'_>, arg: *mut c_void) -> UnwindReasonCode {
+        // SAFETY: The argument points to the frame counter below.
+        let nr_frames = unsafe { &mut *(arg as *mut usize) };
+        *nr_frames += 1;
+        // The first frame is the one of this function.
+        if *nr_frames == 1 {
+            return UnwindReasonCode::NO_REASON;
+        }
+
+        early_print!("Registers at pc {:#x}:", _Unwind_GetIP(unwind_ctx));
+        for i in 0..NR_REGS {
+            if i % 4 == 0 {
+                early_print!("\n    ");
+            }
+            let reg_i = _Unwind_GetGR(unwind_ctx, i as i32);
+            early_print!(" {:>4} {:#18x};", register_name(i), reg_i);
+        }
+        early_print!("\n\n");
+        UnwindReasonCode::NORMAL_STOP
+    }
+
+    let mut nr_frames = 0usize;
+    _Unwind_Backtrace(callback, &mut nr_frames as *mut _ as _);
+}
+
+/// Returns the name of the register whose number follows the DWARF standard.
+fn register_name(i: u16) -> &'static str {
+    cfg_if::cfg_if! {
+        if #[cfg(target_arch = "x86_64")] {
+            gimli::X86_64::register_name(Register(i)).unwrap_or("unknown")
+        } else if #[cfg(target_arch = "riscv64")] {
+            gimli::RiscV::register_name(Register(i)).unwrap_or("unknown")
+        } else if #[cfg(target_arch = "aarch64")] {
+            gimli::AArch64::register_name(Register(i)).unwrap_or("unknown")
+        } else {
+            "unknown"
+        }
+    }
 }
 
 /// Prints the stack trace of the current thread to the console.
@@ -59,7 +279,12 @@ pub fn print_stack_trace() {
     /// We acquire a global lock to prevent the frames in the stack trace from
     /// interleaving. The spin lock is used merely for its simplicity.
     static BACKTRACE_PRINT_LOCK: SpinLock<()> = SpinLock::new(());
-    let _lock = BACKTRACE_PRINT_LOCK.lock();
+    // The lock may be held forever by a stopped CPU when the kernel is panicking.
+    let _lock = if is_panicking() {
+        BACKTRACE_PRINT_LOCK.try_lock()
+    } else {
+        Some(BACKTRACE_PRINT_LOCK.lock())
+    };
 
     early_println!("Printing stack trace:");
 
@@ -78,22 +303,21 @@ pub fn print_stack_trace() {
                 fde_initial_address,
                 pc,
             );
+            // The PC is a return address except in the innermost frame, so the symbol is found
+            // with the byte before it, which belongs to the call instruction.
+            if let Some(symbol) = crate::kallsyms::symbol_for_addr(pc - 1) {
+                early_print!("      at {}+{:#x}", symbol.name(), pc - symbol.addr());
+                if let Some(module_name) = symbol.module_name() {
+                    early_print!(" [{}]", module_name);
+                }
+                early_print!("\n");
+            }
         }
         // Print the first 8 general registers for any architecture. The register number follows
         // the DWARF standard.
         for i in 0..8u16 {
             let reg_i = _Unwind_GetGR(unwind_ctx, i as i32);
-            cfg_if::cfg_if! {
-                if #[cfg(target_arch = "x86_64")] {
-                    let reg_name = gimli::X86_64::register_name(Register(i)).unwrap_or("unknown");
-                } else if #[cfg(target_arch = "riscv64")] {
-                    let reg_name = gimli::RiscV::register_name(Register(i)).unwrap_or("unknown");
-                } else if #[cfg(target_arch = "aarch64")] {
-                    let reg_name = gimli::AArch64::register_name(Register(i)).unwrap_or("unknown");
-                } else {
-                    let reg_name = "unknown";
-                }
-            }
+            let reg_name = register_name(i);
             if i % 4 == 0 {
                 early_print!("\n    ");
             }
@@ -106,3 +330,20 @@ pub fn print_stack_trace() {
     let mut data = CallbackData { counter: 0 };
     _Unwind_Backtrace(callback, &mut data as *mut _ as _);
 }
+
+#[cfg(ktest)]
+mod test {
+    use super::*;
+    use crate::prelude::ktest;
+
+    #[ktest]
+    fn parse_panic_policy() {
+        assert_eq!(PanicPolicy::parse("exit"), Some(PanicPolicy::Exit));
+        assert_eq!(PanicPolicy::parse("halt"), Some(PanicPolicy::Halt));
+        assert_eq!(PanicPolicy::parse("reboot"), Some(PanicPolicy::Reboot(0)));
+        assert_eq!(PanicPolicy::parse("0"), Some(PanicPolicy::Halt));
+        assert_eq!(PanicPolicy::parse("10"), Some(PanicPolicy::Reboot(10)));
+        assert_eq!(PanicPolicy::parse("-1"), Some(PanicPolicy::Reboot(0)));
+        assert_eq!(PanicPolicy::parse("never"), None);
+    }
+}
@@ -0,0 +1,76 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! System sleep states.
+//!
+//! Suspend-to-RAM keeps the memory powered while the CPUs and the devices are powered off, so
+//! the devices must be quiesced before suspending and be reinitialized after resuming. A device
+//! driver registers a [`DevicePm`] with [`register_device`], whose callbacks are invoked by
+//! [`suspend_to_ram`]: the devices are suspended in the reverse order of the registration,
+//! so a device is suspended before the devices that it depends on (e.g., its bus), and resumed
+//! in the order of the registration.
+//!
+//! The user tasks should be frozen before suspending, which is the job of the kernel.
+
+use alloc::string::{String, ToString};
+
+use crate::{arch, cpu::num_cpus, prelude::*, sync::Mutex, trap::disable_local, Error};
+
+/// The power management callbacks of a device.
+pub trait DevicePm: Send + Sync {
+    /// Quiesces the device before the system is suspended.
+    ///
+    /// The device must not issue any requests or interrupts after it succeeds. If it fails,
+    /// the suspend is aborted, and the devices that have been suspended are resumed.
+    fn suspend(&self) -> Result<()>;
+
+    /// Reinitializes the device after the system is resumed.
+    fn resume(&self);
+}
+
+static DEVICES: Mutex<Vec<(String, Arc<dyn DevicePm>)>> = Mutex::new(Vec::new());
+
+/// Registers the power management callbacks of a device.
+pub fn register_device(name: &str, device: Arc<dyn DevicePm>) {
+    DEVICES.lock().push((name.to_string(), device));
+}
+
+/// Returns whether the platform supports suspend-to-RAM.
+pub fn is_suspend_supported() -> bool {
+    arch::power::is_supported()
+}
+
+/// Suspends the system to RAM, and returns after the system resumes.
+///
+/// The system can only be suspended if there is only one CPU, since the other CPUs cannot be
+/// stopped yet.
+///
+/// # Errors
+///
+/// This function returns [`Error::InvalidArgs`] if the system cannot be suspended, or the
+/// error of the device that fails to suspend.
+pub fn suspend_to_ram() -> Result<()> {
+    if !is_suspend_supported() || num_cpus() > 1 {
+        return Err(Error::InvalidArgs);
+    }
+
+    let devices = DEVICES.lock();
+    for (index, (name, device)) in devices.iter().enumerate().rev() {
+        if let Err(err) = device.suspend() {
+            log::warn!("Device {} fails to suspend: {:?}", name, err);
+            for (_, device) in devices[index + 1..].iter() {
+                device.resume();
+            }
+            return Err(err);
+        }
+    }
+
+    let irq_guard = disable_local();
+    // SAFETY: The local IRQs are disabled, and there are no other CPUs.
+    let result = unsafe { arch::power::suspend() };
+    drop(irq_guard);
+
+    for (_, device) in devices.iter() {
+        device.resume();
+    }
+    result
+}
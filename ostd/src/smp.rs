@@ -69,8 +69,10 @@ fn do_inter_processor_call(_trapframe: &TrapFrame) {
     let preempt_guard = trap::disable_local();
     let cur_cpu = preempt_guard.current_cpu();
 
-    let mut queue = CALL_QUEUES.get_on_cpu(cur_cpu).lock();
-    while let Some(f) = queue.pop_front() {
+    // The queue is not locked while calling the functions, since a function may never return
+    // (e.g., the one that stops the CPU when the kernel panics).
+    let queue = CALL_QUEUES.get_on_cpu(cur_cpu);
+    while let Some(f) = queue.lock().pop_front() {
         log::trace!(
             "Performing inter-processor call to {:#?} on CPU {:#?}",
             f,
@@ -80,6 +82,11 @@ fn do_inter_processor_call(_trapframe: &TrapFrame) {
     }
 }
 
+/// Returns whether the inter-processor calls can be made.
+pub(crate) fn is_initialized() -> bool {
+    INTER_PROCESSOR_CALL_IRQ.get().is_some()
+}
+
 pub(super) fn init() {
     let mut irq = IrqLine::alloc().unwrap();
     irq.on_active(do_inter_processor_call);
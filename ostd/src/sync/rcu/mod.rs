@@ -20,6 +20,7 @@ use crate::task::{atomic_mode::AsAtomicModeGuard, disable_preempt, DisabledPreem
 mod monitor;
 mod owner_ptr;
 
+pub use monitor::GracePeriodInfo;
 pub use owner_ptr::OwnerPtr;
 
 /// A Read-Copy Update (RCU) cell for sharing a pointer between threads.
@@ -467,6 +468,13 @@ pub unsafe fn finish_grace_period() {
     }
 }
 
+/// Returns the information of the current grace period, if it is not complete.
+///
+/// This can be used to detect the CPUs that stall the grace periods.
+pub fn current_grace_period() -> Option<GracePeriodInfo> {
+    RCU_MONITOR.get()?.current_grace_period()
+}
+
 static RCU_MONITOR: Once<RcuMonitor> = Once::new();
 
 pub fn init() {
@@ -11,6 +11,7 @@ use crate::{
     prelude::*,
     sync::SpinLock,
     task::atomic_mode::AsAtomicModeGuard,
+    timer::Jiffies,
 };
 
 /// A RCU monitor ensures the completion of _grace periods_ by keeping track
@@ -89,6 +90,41 @@ impl RcuMonitor {
         state.current_gp.restart(callbacks);
         self.is_monitoring.store(true, Relaxed);
     }
+
+    /// Returns the information of the current grace period, if it is not complete.
+    pub fn current_grace_period(&self) -> Option<GracePeriodInfo> {
+        if !self.is_monitoring.load(Relaxed) {
+            return None;
+        }
+
+        let state = self.state.disable_irq().lock();
+        let gp = &state.current_gp;
+        if gp.is_complete() {
+            return None;
+        }
+
+        let passed_cpus = gp.cpu_mask.load();
+        let mut pending_cpus = CpuSet::new_full();
+        for cpu in passed_cpus.iter() {
+            pending_cpus.remove(cpu);
+        }
+        Some(GracePeriodInfo {
+            seq: gp.seq,
+            start: gp.start,
+            pending_cpus,
+        })
+    }
+}
+
+/// The information of an ongoing grace period.
+#[derive(Clone, Debug)]
+pub struct GracePeriodInfo {
+    /// The sequence number of the grace period, which increases for each grace period.
+    pub seq: u64,
+    /// The time when the grace period started.
+    pub start: Jiffies,
+    /// The CPUs that have not passed a quiescent state in the grace period.
+    pub pending_cpus: CpuSet,
 }
 
 struct State {
@@ -111,6 +147,8 @@ struct GracePeriod {
     callbacks: Callbacks,
     cpu_mask: AtomicCpuSet,
     is_complete: bool,
+    seq: u64,
+    start: Jiffies,
 }
 
 impl GracePeriod {
@@ -119,6 +157,8 @@ impl GracePeriod {
             callbacks: Callbacks::new(),
             cpu_mask: AtomicCpuSet::new(CpuSet::new_empty()),
             is_complete: true,
+            seq: 0,
+            start: Jiffies::new(0),
         }
     }
 
@@ -142,5 +182,7 @@ impl GracePeriod {
         self.is_complete = false;
         self.cpu_mask.store(&CpuSet::new_empty());
         self.callbacks = callbacks;
+        self.seq += 1;
+        self.start = Jiffies::elapsed();
     }
 }
@@ -126,6 +126,10 @@ pub(super) unsafe fn after_switching_to() {
 
         Some(prev_task)
     } else {
+        // We have just switched from the boot context, which is never resumed.
+        // SAFETY: The boot context is left only once on each CPU.
+        unsafe { crate::boot::smp::free_boot_stack() };
+
         None
     };
 
@@ -0,0 +1,254 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The trace buffer.
+//!
+//! Each CPU records its events in its own ring of fixed capacity, so that recording never
+//! contends with other CPUs. If a ring is full, its oldest event is overwritten. The readers
+//! merge the rings by the timestamps, which are read from the TSC.
+
+use alloc::vec::Vec;
+use core::{
+    fmt,
+    mem::size_of,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use spin::Once;
+
+use super::{Tracepoint, MAX_NR_ARGS};
+use crate::{
+    arch::{read_tsc, tsc_freq},
+    cpu::{all_cpus, CpuId, PinCurrentCpu},
+    cpu_local,
+    mm::valloc::VAlloc,
+    sync::{LocalIrqDisabled, SpinLock},
+    timer,
+};
+
+/// The number of the events that the ring of each CPU can hold.
+const NR_EVENTS_PER_CPU: usize = 4096;
+
+/// An event read from the trace buffer.
+#[derive(Clone, Copy)]
+pub struct TraceEvent {
+    tracepoint: &'static Tracepoint,
+    cpu: CpuId,
+    timestamp: u64,
+    args: [u64; MAX_NR_ARGS],
+}
+
+impl TraceEvent {
+    /// Returns the tracepoint that records the event.
+    pub fn tracepoint(&self) -> &'static Tracepoint {
+        self.tracepoint
+    }
+
+    /// Returns the CPU where the event happens.
+    pub fn cpu(&self) -> CpuId {
+        self.cpu
+    }
+
+    /// Returns the time since boot when the event happens.
+    pub fn timestamp(&self) -> Duration {
+        let nanos = self.timestamp as u128 * 1_000_000_000 / tsc_freq() as u128;
+        Duration::from_nanos(nanos as u64)
+    }
+
+    /// Returns the arguments of the event.
+    pub fn args(&self) -> &[u64; MAX_NR_ARGS] {
+        &self.args
+    }
+
+    /// Formats the arguments of the event with the format of its tracepoint.
+    pub fn format_args(&self, f: &mut dyn fmt::Write) -> fmt::Result {
+        self.tracepoint.format_args(&self.args, f)
+    }
+}
+
+impl fmt::Debug for TraceEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TraceEvent")
+            .field("tracepoint", &self.tracepoint.name())
+            .field("cpu", &self.cpu)
+            .field("timestamp", &self.timestamp)
+            .field("args", &self.args)
+            .finish()
+    }
+}
+
+struct TraceRing {
+    /// The storage of the events, which is allocated in [`init`].
+    ///
+    /// It is virtually allocated, since the rings of many CPUs need a lot of memory.
+    events: Option<VAlloc>,
+    /// The number of the events that have been consumed or overwritten
+    head: u64,
+    /// The number of the events that have been recorded
+    tail: u64,
+    nr_overruns: u64,
+}
+
+impl TraceRing {
+    const fn new() -> Self {
+        Self {
+            events: None,
+            head: 0,
+            tail: 0,
+            nr_overruns: 0,
+        }
+    }
+
+    fn push(&mut self, event: TraceEvent) {
+        let Some(events) = self.events.as_ref() else {
+            return;
+        };
+
+        let index = (self.tail % NR_EVENTS_PER_CPU as u64) as usize;
+        let ptr = events.as_mut_ptr().cast::<TraceEvent>();
+        // SAFETY: The storage can hold `NR_EVENTS_PER_CPU` events and is only
+        // accessed with the raw pointer, while the ring is locked. The event
+        // is `Copy`, so the overwritten event needs not to be dropped.
+        unsafe { ptr.add(index).write(event) };
+        self.tail += 1;
+
+        if self.tail - self.head > NR_EVENTS_PER_CPU as u64 {
+            self.head += 1;
+            self.nr_overruns += 1;
+        }
+    }
+
+    fn get(&self, pos: u64) -> &TraceEvent {
+        debug_assert!(self.head <= pos && pos < self.tail);
+
+        let index = (pos % NR_EVENTS_PER_CPU as u64) as usize;
+        let ptr = self
+            .events
+            .as_ref()
+            .unwrap()
+            .as_mut_ptr()
+            .cast::<TraceEvent>();
+        // SAFETY: The event at `pos` has been written by `push`, and can only
+        // be overwritten with `&mut self`.
+        unsafe { &*ptr.add(index) }
+    }
+
+    fn peek(&self) -> Option<&TraceEvent> {
+        (self.head < self.tail).then(|| self.get(self.head))
+    }
+}
+
+cpu_local! {
+    static RINGS: SpinLock<TraceRing, LocalIrqDisabled> = SpinLock::new(TraceRing::new());
+}
+
+static IS_TRACING_ON: AtomicBool = AtomicBool::new(true);
+
+static HAS_NEW_EVENTS: AtomicBool = AtomicBool::new(false);
+
+static NOTIFIER: Once<fn()> = Once::new();
+
+pub(super) fn init() {
+    for cpu in all_cpus() {
+        let events = VAlloc::new(NR_EVENTS_PER_CPU * size_of::<TraceEvent>()).unwrap();
+        RINGS.get_on_cpu(cpu).lock().events = Some(events);
+    }
+    timer::register_callback(notify_readers);
+}
+
+pub(super) fn record(tracepoint: &'static Tracepoint, args: &[u64]) {
+    if !is_tracing_on() {
+        return;
+    }
+
+    let irq_guard = crate::trap::disable_local();
+    let cpu = irq_guard.current_cpu();
+    let mut event = TraceEvent {
+        tracepoint,
+        cpu,
+        timestamp: read_tsc(),
+        args: [0; MAX_NR_ARGS],
+    };
+    let nr_args = args.len().min(MAX_NR_ARGS);
+    event.args[..nr_args].copy_from_slice(&args[..nr_args]);
+
+    RINGS.get_with(&irq_guard).lock().push(event);
+    HAS_NEW_EVENTS.store(true, Ordering::Release);
+}
+
+/// Returns whether the events are being recorded.
+pub fn is_tracing_on() -> bool {
+    IS_TRACING_ON.load(Ordering::Relaxed)
+}
+
+/// Starts or stops recording the events, without changing the enabled tracepoints.
+pub fn set_tracing_on(is_on: bool) {
+    IS_TRACING_ON.store(is_on, Ordering::Relaxed);
+}
+
+/// Reads all the events in the trace buffer without consuming them.
+///
+/// The events are sorted by their timestamps.
+pub fn read_events() -> Vec<TraceEvent> {
+    let mut events = Vec::new();
+    for cpu in all_cpus() {
+        let ring = RINGS.get_on_cpu(cpu).lock();
+        events.extend((ring.head..ring.tail).map(|pos| *ring.get(pos)));
+    }
+    events.sort_by_key(|event| event.timestamp);
+    events
+}
+
+/// Consumes the oldest event in the trace buffer, if any.
+pub fn consume_event() -> Option<TraceEvent> {
+    let oldest_cpu = all_cpus()
+        .filter_map(|cpu| {
+            let ring = RINGS.get_on_cpu(cpu).lock();
+            ring.peek().map(|event| (cpu, event.timestamp))
+        })
+        .min_by_key(|(_, timestamp)| *timestamp)?
+        .0;
+
+    // The oldest event may be overwritten in the meantime, so the next one is consumed.
+    let mut ring = RINGS.get_on_cpu(oldest_cpu).lock();
+    let event = *ring.peek()?;
+    ring.head += 1;
+    Some(event)
+}
+
+/// Discards all the events in the trace buffer.
+pub fn clear() {
+    for cpu in all_cpus() {
+        let mut ring = RINGS.get_on_cpu(cpu).lock();
+        ring.head = ring.tail;
+        ring.nr_overruns = 0;
+    }
+}
+
+/// Returns the number of the events that the trace buffer of each CPU can hold.
+pub fn buffer_entries() -> usize {
+    NR_EVENTS_PER_CPU
+}
+
+/// Returns the number of the events on the CPU that are overwritten before being read.
+pub fn nr_overruns(cpu: CpuId) -> u64 {
+    RINGS.get_on_cpu(cpu).lock().nr_overruns
+}
+
+/// Registers a function that is called after new events are recorded.
+///
+/// The function is called in the timer interrupt handler instead of the tracepoints, since
+/// the tracepoints may be hit with arbitrary locks held. Only one function can be registered,
+/// and subsequent registrations have no effect.
+pub fn register_notifier(notifier: fn()) {
+    NOTIFIER.call_once(|| notifier);
+}
+
+fn notify_readers() {
+    if !HAS_NEW_EVENTS.swap(false, Ordering::Acquire) {
+        return;
+    }
+    if let Some(notifier) = NOTIFIER.get() {
+        notifier();
+    }
+}
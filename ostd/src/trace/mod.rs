@@ -0,0 +1,207 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Static tracepoints and the trace buffer.
+//!
+//! A tracepoint is a static hook placed at an interesting point of the code, e.g., the
+//! context switches or the interrupt handlers. Each tracepoint is disabled by default and
+//! costs only an atomic load when it is hit. Once enabled, each hit records an event with a
+//! few integer arguments and a timestamp in the trace buffer of the current CPU (see
+//! [`read_events`] and [`consume_event`]). The arguments are formatted only when the events
+//! are read, so recording an event is cheap and never allocates memory.
+//!
+//! The tracepoints in OSTD are registered during the initialization. The tracepoints out of
+//! OSTD should be registered with [`register_tracepoint`] before they can be discovered
+//! (e.g., by a tracefs-like interface).
+//!
+//! The arguments that are code addresses can be formatted with their symbols with
+//! [`SymbolizedAddr`](crate::kallsyms::SymbolizedAddr).
+//!
+//! Besides recording events, probes can be attached to a tracepoint with
+//! [`Tracepoint::attach_probe`], which are called with the arguments whenever the tracepoint
+//! is hit, regardless of whether the tracepoint is enabled.
+
+mod buffer;
+
+use alloc::{sync::Arc, vec::Vec};
+use core::{
+    fmt,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+pub use self::buffer::{
+    buffer_entries, clear, consume_event, is_tracing_on, nr_overruns, read_events,
+    register_notifier, set_tracing_on, TraceEvent,
+};
+use crate::sync::{LocalIrqDisabled, SpinLock};
+
+/// The maximum number of the arguments of an event.
+pub const MAX_NR_ARGS: usize = 8;
+
+/// The function that formats the arguments of an event.
+pub type TraceFormatFn = fn(&[u64; MAX_NR_ARGS], &mut dyn fmt::Write) -> fmt::Result;
+
+/// A probe attached to a tracepoint, which is called with the arguments of each hit.
+///
+/// The probe may be called in any context (e.g., in interrupt handlers), so it must not
+/// sleep. It must not hit the tracepoint that it is attached to, either.
+pub type TracepointProbe = Arc<dyn Fn(&[u64; MAX_NR_ARGS]) + Send + Sync>;
+
+/// A static tracepoint.
+///
+/// # Examples
+///
+/// ```
+/// use ostd::trace::Tracepoint;
+///
+/// static MY_EVENT: Tracepoint = Tracepoint::new("my_system", "my_event", |args, f| {
+///     write!(f, "value={}", args[0])
+/// });
+///
+/// MY_EVENT.emit(&[42]);
+/// ```
+pub struct Tracepoint {
+    system: &'static str,
+    name: &'static str,
+    format: TraceFormatFn,
+    is_enabled: AtomicBool,
+    has_probes: AtomicBool,
+    probes: SpinLock<Vec<TracepointProbe>, LocalIrqDisabled>,
+}
+
+impl Tracepoint {
+    /// Creates a disabled tracepoint.
+    ///
+    /// The tracepoint is identified by the name of its system (i.e., the subsystem that the
+    /// tracepoint belongs to, such as "sched" or "irq") and its own name.
+    pub const fn new(system: &'static str, name: &'static str, format: TraceFormatFn) -> Self {
+        Self {
+            system,
+            name,
+            format,
+            is_enabled: AtomicBool::new(false),
+            has_probes: AtomicBool::new(false),
+            probes: SpinLock::new(Vec::new()),
+        }
+    }
+
+    /// Returns the name of the system that the tracepoint belongs to.
+    pub fn system(&self) -> &'static str {
+        self.system
+    }
+
+    /// Returns the name of the tracepoint.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns whether the tracepoint is enabled.
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.is_enabled.load(Ordering::Relaxed)
+    }
+
+    /// Enables or disables the tracepoint.
+    pub fn set_enabled(&self, is_enabled: bool) {
+        self.is_enabled.store(is_enabled, Ordering::Relaxed);
+    }
+
+    /// Attaches a probe to the tracepoint.
+    pub fn attach_probe(&self, probe: TracepointProbe) {
+        let mut probes = self.probes.lock();
+        probes.push(probe);
+        self.has_probes.store(true, Ordering::Relaxed);
+    }
+
+    /// Detaches a probe that is attached by [`Self::attach_probe`].
+    ///
+    /// Returns whether the probe was attached. Once the method returns, the probe is no
+    /// longer called.
+    pub fn detach_probe(&self, probe: &TracepointProbe) -> bool {
+        let mut probes = self.probes.lock();
+        let Some(index) = probes
+            .iter()
+            .position(|attached| Arc::ptr_eq(attached, probe))
+        else {
+            return false;
+        };
+        probes.remove(index);
+        self.has_probes.store(!probes.is_empty(), Ordering::Relaxed);
+        true
+    }
+
+    /// Records an event with the arguments if the tracepoint is enabled, and calls the
+    /// attached probes.
+    ///
+    /// At most [`MAX_NR_ARGS`] arguments are recorded, and the missing ones are zeros.
+    #[inline]
+    pub fn emit(&'static self, args: &[u64]) {
+        if self.is_enabled() {
+            buffer::record(self, args);
+        }
+        if self.has_probes.load(Ordering::Relaxed) {
+            self.call_probes(args);
+        }
+    }
+
+    #[cold]
+    fn call_probes(&self, args: &[u64]) {
+        let mut padded_args = [0; MAX_NR_ARGS];
+        let len = args.len().min(MAX_NR_ARGS);
+        padded_args[..len].copy_from_slice(&args[..len]);
+
+        for probe in self.probes.lock().iter() {
+            probe(&padded_args);
+        }
+    }
+
+    fn format_args(&self, args: &[u64; MAX_NR_ARGS], f: &mut dyn fmt::Write) -> fmt::Result {
+        (self.format)(args, f)
+    }
+}
+
+impl fmt::Debug for Tracepoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tracepoint")
+            .field("system", &self.system)
+            .field("name", &self.name)
+            .field("is_enabled", &self.is_enabled())
+            .finish()
+    }
+}
+
+static TRACEPOINTS: SpinLock<Vec<&'static Tracepoint>> = SpinLock::new(Vec::new());
+
+/// Registers a tracepoint, so that it can be found by [`tracepoints`].
+///
+/// Registering a tracepoint more than once has no effect.
+pub fn register_tracepoint(tracepoint: &'static Tracepoint) {
+    let mut tracepoints = TRACEPOINTS.lock();
+    if !tracepoints
+        .iter()
+        .any(|registered| core::ptr::eq(*registered, tracepoint))
+    {
+        tracepoints.push(tracepoint);
+    }
+}
+
+/// Returns all the registered tracepoints.
+pub fn tracepoints() -> Vec<&'static Tracepoint> {
+    TRACEPOINTS.lock().clone()
+}
+
+/// The tracepoint hit when an interrupt handler starts.
+pub static IRQ_HANDLER_ENTRY: Tracepoint =
+    Tracepoint::new("irq", "irq_handler_entry", |args, f| {
+        write!(f, "irq={}", args[0])
+    });
+
+/// The tracepoint hit when an interrupt handler finishes.
+pub static IRQ_HANDLER_EXIT: Tracepoint = Tracepoint::new("irq", "irq_handler_exit", |args, f| {
+    write!(f, "irq={}", args[0])
+});
+
+pub(crate) fn init() {
+    buffer::init();
+    register_tracepoint(&IRQ_HANDLER_ENTRY);
+    register_tracepoint(&IRQ_HANDLER_EXIT);
+}
@@ -1,8 +1,17 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use core::sync::atomic::{AtomicU64, Ordering};
+
 use spin::Once;
 
-use crate::{arch::irq::IRQ_LIST, cpu_local_cell, task::disable_preempt, trap::TrapFrame};
+use crate::{
+    arch::{irq::IRQ_LIST, read_tsc},
+    cpu::{CpuId, PinCurrentCpu},
+    cpu_local, cpu_local_cell,
+    task::disable_preempt,
+    trace::{IRQ_HANDLER_ENTRY, IRQ_HANDLER_EXIT},
+    trap::TrapFrame,
+};
 
 static BOTTOM_HALF_HANDLER: Once<fn()> = Once::new();
 
@@ -21,11 +30,17 @@ pub fn register_bottom_half_handler(func: fn()) {
 }
 
 fn process_top_half(trap_frame: &TrapFrame, irq_number: usize) {
+    let last_frame = INTERRUPTED_FRAME.load();
+    INTERRUPTED_FRAME.store(trap_frame as *const TrapFrame);
+
     let irq_line = IRQ_LIST.get().unwrap().get(irq_number).unwrap();
     let callback_functions = irq_line.callback_list();
     for callback_function in callback_functions.iter() {
         callback_function.call(trap_frame);
     }
+    drop(callback_functions);
+
+    INTERRUPTED_FRAME.store(last_frame);
 }
 
 fn process_bottom_half() {
@@ -59,11 +74,31 @@ pub(crate) fn call_irq_callback_functions(trap_frame: &TrapFrame, irq_number: us
     // bottom half cannot be reentrant for the same reason.
     INTERRUPT_NESTED_LEVEL.add_assign(1);
 
+    // The task cannot migrate to another CPU during the interrupt handling.
+    let cpu = crate::cpu::current_cpu_racy();
+    let start = read_tsc();
+    IRQ_COUNTS.get_on_cpu(cpu)[irq_number].fetch_add(1, Ordering::Relaxed);
+    IRQ_HANDLER_ENTRY.emit(&[irq_number as u64]);
     process_top_half(trap_frame, irq_number);
+    IRQ_HANDLER_EXIT.emit(&[irq_number as u64]);
     crate::arch::interrupts_ack(irq_number);
+    HARDIRQ_CYCLES
+        .get_on_cpu(cpu)
+        .fetch_add(read_tsc().wrapping_sub(start), Ordering::Relaxed);
 
     if INTERRUPT_NESTED_LEVEL.load() == 1 {
+        let start = read_tsc();
+        let hardirq_start = HARDIRQ_CYCLES.get_on_cpu(cpu).load(Ordering::Relaxed);
         process_bottom_half();
+        // The top halves that interrupt the bottom half have been accounted for.
+        let nested_hardirq_cycles =
+            HARDIRQ_CYCLES.get_on_cpu(cpu).load(Ordering::Relaxed) - hardirq_start;
+        let softirq_cycles = read_tsc()
+            .wrapping_sub(start)
+            .saturating_sub(nested_hardirq_cycles);
+        SOFTIRQ_CYCLES
+            .get_on_cpu(cpu)
+            .fetch_add(softirq_cycles, Ordering::Relaxed);
     }
 
     INTERRUPT_NESTED_LEVEL.sub_assign(1);
@@ -71,6 +106,45 @@ pub(crate) fn call_irq_callback_functions(trap_frame: &TrapFrame, irq_number: us
 
 cpu_local_cell! {
     static INTERRUPT_NESTED_LEVEL: u8 = 0;
+    /// The trap frame of the context interrupted by the running top half, if any.
+    static INTERRUPTED_FRAME: *const TrapFrame = core::ptr::null();
+}
+
+cpu_local! {
+    /// The TSC cycles spent on the interrupt top halves.
+    static HARDIRQ_CYCLES: AtomicU64 = AtomicU64::new(0);
+    /// The TSC cycles spent on the interrupt bottom halves.
+    static SOFTIRQ_CYCLES: AtomicU64 = AtomicU64::new(0);
+    /// The number of the interrupts dispatched to each IRQ line.
+    static IRQ_COUNTS: [AtomicU64; NR_IRQ_LINES] = [const { AtomicU64::new(0) }; NR_IRQ_LINES];
+}
+
+/// The number of the IRQ lines, which are numbered by `u8`.
+const NR_IRQ_LINES: usize = 256;
+
+/// Returns the number of the interrupts that have been dispatched to the IRQ line on the CPU.
+pub fn irq_count(irq_num: u8, cpu: CpuId) -> u64 {
+    IRQ_COUNTS.get_on_cpu(cpu)[irq_num as usize].load(Ordering::Relaxed)
+}
+
+/// Returns the TSC cycles that the CPU has spent on the interrupt top halves.
+pub fn hardirq_cycles(cpu: CpuId) -> u64 {
+    HARDIRQ_CYCLES.get_on_cpu(cpu).load(Ordering::Relaxed)
+}
+
+/// Returns the TSC cycles that the CPU has spent on the interrupt bottom halves.
+pub fn softirq_cycles(cpu: CpuId) -> u64 {
+    SOFTIRQ_CYCLES.get_on_cpu(cpu).load(Ordering::Relaxed)
+}
+
+/// Returns the TSC cycles that the current CPU has spent on handling interrupts.
+///
+/// The interrupt time is not charged to the interrupted tasks, so the caller can use the
+/// difference of the returned values to exclude the interrupt time from a time interval.
+pub fn current_irq_cycles() -> u64 {
+    let preempt_guard = disable_preempt();
+    let cpu = preempt_guard.current_cpu();
+    hardirq_cycles(cpu) + softirq_cycles(cpu)
 }
 
 /// Returns whether we are in the interrupt context.
@@ -79,3 +153,24 @@ cpu_local_cell! {
 pub fn in_interrupt_context() -> bool {
     INTERRUPT_NESTED_LEVEL.load() != 0
 }
+
+/// Prints the registers and the stack trace of the context interrupted by the current
+/// interrupt to the console.
+///
+/// If the function is not called in the top half of an interrupt handler, only the stack
+/// trace of the current context is printed. This is useful to diagnose a stuck CPU from
+/// its own interrupt handlers (e.g., the timer interrupt or an inter-processor call).
+pub fn dump_interrupted_context() {
+    let irq_guard = crate::trap::disable_local();
+    let cpu = irq_guard.current_cpu();
+
+    let frame = INTERRUPTED_FRAME.load();
+    if !frame.is_null() {
+        // SAFETY: The pointer is set only when the top halves are running on the current CPU,
+        // during which the trap frame is alive. The local IRQs are disabled, so the running top
+        // half cannot be preempted by another one.
+        let frame = unsafe { &*frame };
+        crate::early_println!("CPU #{} was interrupted at {:#x?}", cpu.as_usize(), frame);
+    }
+    crate::panic::print_stack_trace();
+}
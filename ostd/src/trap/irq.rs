@@ -1,19 +1,38 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use core::fmt::Debug;
+use core::{
+    fmt::Debug,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use spin::Once;
 
 use crate::{
     arch::irq::{self, IrqCallbackHandle, IRQ_ALLOCATOR},
+    cpu::{all_cpus, CpuSet},
     prelude::*,
-    sync::GuardTransfer,
+    sync::{GuardTransfer, WaitQueue},
     task::atomic_mode::InAtomicMode,
-    trap::TrapFrame,
+    trap::{irq_count, TrapFrame},
     Error,
 };
 
 /// Type alias for the irq callback function.
 pub type IrqCallbackFunction = dyn Fn(&TrapFrame) + Sync + Send + 'static;
 
+static IRQ_THREAD_SPAWNER: Once<fn(Box<dyn FnOnce() + Send>)> = Once::new();
+
+/// Injects a function that spawns a kernel thread to run the function.
+///
+/// The function is used to spawn the threads of the threaded handlers (see
+/// [`IrqLine::on_active_threaded`]), since the kernel threads are managed by the scheduler of
+/// the kernel instead of OSTD.
+///
+/// This function can only be called once. Subsequent calls will do nothing.
+pub fn inject_irq_thread_spawner(spawner: fn(Box<dyn FnOnce() + Send>)) {
+    IRQ_THREAD_SPAWNER.call_once(|| spawner);
+}
+
 /// An Interrupt ReQuest(IRQ) line. User can use [`alloc`] or [`alloc_specific`] to get specific IRQ line.
 ///
 /// The IRQ number is guaranteed to be external IRQ number and user can register callback functions to this IRQ resource.
@@ -28,6 +47,7 @@ pub struct IrqLine {
     #[expect(clippy::redundant_allocation)]
     inner_irq: Arc<&'static irq::IrqLine>,
     callbacks: Vec<IrqCallbackHandle>,
+    threads: Vec<Arc<IrqThread>>,
 }
 
 impl IrqLine {
@@ -57,6 +77,7 @@ impl IrqLine {
             irq_num,
             inner_irq: unsafe { irq::IrqLine::acquire(irq_num) },
             callbacks: Vec::new(),
+            threads: Vec::new(),
         }
     }
 
@@ -75,11 +96,91 @@ impl IrqLine {
         self.callbacks.push(self.inner_irq.on_active(callback))
     }
 
+    /// Registers a threaded handler that will be invoked when the IRQ is active.
+    ///
+    /// The hard handler is invoked in the interrupt context. It should only do the minimum
+    /// work to silence the device (e.g., acknowledging the interrupt). The IRQ line is then
+    /// masked, and the thread handler is invoked in a dedicated kernel thread, after which
+    /// the IRQ line is unmasked. This keeps the slow handlers (e.g., those of the SD
+    /// controllers) from delaying the other interrupts.
+    ///
+    /// The thread exits when this IRQ line is dropped.
+    ///
+    /// # Errors
+    ///
+    /// This method returns [`Error::InvalidArgs`] if the kernel has not injected a thread
+    /// spawner by [`inject_irq_thread_spawner`].
+    pub fn on_active_threaded<H, T>(&mut self, hard_handler: H, thread_handler: T) -> Result<()>
+    where
+        H: Fn(&TrapFrame) + Sync + Send + 'static,
+        T: Fn() + Send + 'static,
+    {
+        let Some(spawner) = IRQ_THREAD_SPAWNER.get() else {
+            return Err(Error::InvalidArgs);
+        };
+
+        let inner_irq = self.inner_irq();
+        let thread = Arc::new(IrqThread {
+            is_pending: AtomicBool::new(false),
+            is_stopped: AtomicBool::new(false),
+            wait_queue: WaitQueue::new(),
+        });
+
+        let thread_cloned = thread.clone();
+        spawner(Box::new(move || {
+            while thread_cloned.wait_for_pending() {
+                thread_handler();
+                inner_irq.unmask();
+            }
+            // The hard handler may have masked the IRQ line right before it is unregistered.
+            if thread_cloned.is_pending.swap(false, Ordering::AcqRel) {
+                inner_irq.unmask();
+            }
+        }));
+
+        let thread_cloned = thread.clone();
+        let handle = inner_irq.on_active(move |trap_frame| {
+            hard_handler(trap_frame);
+            if !thread_cloned.is_pending.swap(true, Ordering::AcqRel) {
+                inner_irq.mask();
+                thread_cloned.wait_queue.wake_one();
+            }
+        });
+        self.callbacks.push(handle);
+        self.threads.push(thread);
+
+        Ok(())
+    }
+
     /// Checks if there are no registered callbacks.
     pub fn is_empty(&self) -> bool {
         self.callbacks.is_empty()
     }
 
+    /// Returns the number of the interrupts that have been dispatched to the IRQ line on all
+    /// CPUs.
+    pub fn dispatch_count(&self) -> u64 {
+        all_cpus().map(|cpu| irq_count(self.irq_num, cpu)).sum()
+    }
+
+    /// Returns the CPUs to which the interrupts of the IRQ line are delivered.
+    pub fn affinity(&self) -> CpuSet {
+        self.inner_irq.affinity()
+    }
+
+    /// Sets the CPUs to which the interrupts of the IRQ line are delivered.
+    ///
+    /// # Errors
+    ///
+    /// This method returns [`Error::InvalidArgs`] if the set is empty, or if the interrupt
+    /// controller cannot deliver the interrupts of the IRQ line to the CPUs.
+    pub fn set_affinity(&self, cpus: &CpuSet) -> Result<()> {
+        if cpus.is_empty() {
+            return Err(Error::InvalidArgs);
+        }
+        self.inner_irq.set_affinity(cpus)
+    }
+
     pub(crate) fn inner_irq(&self) -> &'static irq::IrqLine {
         &self.inner_irq
     }
@@ -91,12 +192,20 @@ impl Clone for IrqLine {
             irq_num: self.irq_num,
             inner_irq: self.inner_irq.clone(),
             callbacks: Vec::new(),
+            threads: Vec::new(),
         }
     }
 }
 
 impl Drop for IrqLine {
     fn drop(&mut self) {
+        // Unregister the hard handlers first, so that the threads are not woken up again.
+        self.callbacks.clear();
+        for thread in self.threads.iter() {
+            thread.is_stopped.store(true, Ordering::Release);
+            thread.wait_queue.wake_all();
+        }
+
         if Arc::strong_count(&self.inner_irq) == 1 {
             IRQ_ALLOCATOR
                 .get()
@@ -107,6 +216,40 @@ impl Drop for IrqLine {
     }
 }
 
+/// The state of the kernel thread of a threaded handler.
+struct IrqThread {
+    /// Whether the hard handler has run and masked the IRQ line since the thread handler
+    /// started last time.
+    is_pending: AtomicBool,
+    /// Whether the IRQ line has been dropped, after which the thread should exit.
+    is_stopped: AtomicBool,
+    wait_queue: WaitQueue,
+}
+
+impl Debug for IrqThread {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("IrqThread")
+            .field("is_pending", &self.is_pending)
+            .field("is_stopped", &self.is_stopped)
+            .finish_non_exhaustive()
+    }
+}
+
+impl IrqThread {
+    /// Waits until the hard handler has run, and returns `false` if the thread should exit.
+    fn wait_for_pending(&self) -> bool {
+        self.wait_queue.wait_until(|| {
+            if self.is_stopped.load(Ordering::Acquire) {
+                Some(false)
+            } else if self.is_pending.swap(false, Ordering::AcqRel) {
+                Some(true)
+            } else {
+                None
+            }
+        })
+    }
+}
+
 /// Disables all IRQs on the current CPU (i.e., locally).
 ///
 /// This function returns a guard object, which will automatically enable local IRQs again when
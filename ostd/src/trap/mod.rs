@@ -5,8 +5,13 @@
 mod handler;
 mod irq;
 
-pub use handler::{in_interrupt_context, register_bottom_half_handler};
+pub use handler::{
+    current_irq_cycles, dump_interrupted_context, hardirq_cycles, in_interrupt_context, irq_count,
+    register_bottom_half_handler, softirq_cycles,
+};
 
 pub(crate) use self::handler::call_irq_callback_functions;
-pub use self::irq::{disable_local, DisabledLocalIrqGuard, IrqCallbackFunction, IrqLine};
+pub use self::irq::{
+    disable_local, inject_irq_thread_spawner, DisabledLocalIrqGuard, IrqCallbackFunction, IrqLine,
+};
 pub use crate::arch::trap::TrapFrame;
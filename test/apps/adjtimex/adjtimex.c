@@ -0,0 +1,139 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include "../network/test.h"
+#include <sys/timex.h>
+#include <time.h>
+
+#define NSEC_PER_SEC 1000000000L
+
+static long long clock_nsecs(clockid_t clockid)
+{
+	struct timespec ts;
+
+	if (clock_gettime(clockid, &ts) < 0)
+		return -1;
+	return (long long)ts.tv_sec * NSEC_PER_SEC + ts.tv_nsec;
+}
+
+// Returns the real time minus the monotonic time, in milliseconds.
+static long long wall_offset_msecs(void)
+{
+	return (clock_nsecs(CLOCK_REALTIME) - clock_nsecs(CLOCK_MONOTONIC)) /
+	       1000000;
+}
+
+FN_TEST(clock_ids)
+{
+	struct timespec ts;
+	long long monotonic;
+
+	TEST_SUCC(clock_gettime(CLOCK_MONOTONIC_RAW, &ts));
+	TEST_SUCC(clock_gettime(CLOCK_BOOTTIME, &ts));
+	TEST_SUCC(clock_gettime(CLOCK_TAI, &ts));
+	TEST_SUCC(clock_gettime(CLOCK_REALTIME_ALARM, &ts));
+	TEST_SUCC(clock_gettime(CLOCK_BOOTTIME_ALARM, &ts));
+	TEST_ERRNO(clock_gettime(10, &ts), EINVAL);
+
+	monotonic = clock_nsecs(CLOCK_MONOTONIC);
+	TEST_RES(clock_nsecs(CLOCK_BOOTTIME), _ret >= monotonic);
+}
+END_TEST()
+
+FN_TEST(read_state)
+{
+	struct timex tx = { .modes = 0 };
+
+	TEST_RES(adjtimex(&tx), _ret >= TIME_OK && _ret <= TIME_ERROR &&
+					tx.tick == 10000 && tx.precision > 0);
+	TEST_RES(clock_adjtime(CLOCK_REALTIME, &tx), _ret >= TIME_OK);
+	TEST_ERRNO(clock_adjtime(CLOCK_MONOTONIC, &tx), EOPNOTSUPP);
+	TEST_ERRNO(clock_adjtime(12345, &tx), EINVAL);
+}
+END_TEST()
+
+FN_TEST(invalid_modes)
+{
+	struct timex tx = { .modes = ADJ_TICK, .tick = 1 };
+
+	TEST_ERRNO(adjtimex(&tx), EINVAL);
+
+	tx.modes = ADJ_OFFSET_SINGLESHOT | ADJ_FREQUENCY;
+	TEST_ERRNO(adjtimex(&tx), EINVAL);
+
+	tx.modes = ADJ_SETOFFSET;
+	tx.time.tv_sec = 0;
+	tx.time.tv_usec = 1000000;
+	TEST_ERRNO(adjtimex(&tx), EINVAL);
+}
+END_TEST()
+
+FN_TEST(frequency)
+{
+	struct timex tx = { .modes = ADJ_FREQUENCY, .freq = 100 << 16 };
+
+	TEST_RES(adjtimex(&tx), tx.freq == 100 << 16);
+
+	// The frequency is clamped to 500 ppm.
+	tx.freq = 1000 << 16;
+	TEST_RES(adjtimex(&tx), tx.freq == 500 << 16);
+
+	tx.freq = 0;
+	TEST_RES(adjtimex(&tx), tx.freq == 0);
+}
+END_TEST()
+
+FN_TEST(singleshot_offset)
+{
+	struct timex tx = { .modes = ADJ_OFFSET_SINGLESHOT, .offset = 10000 };
+
+	TEST_SUCC(adjtimex(&tx));
+
+	// The offset is being slewed, so less than the whole offset remains.
+	tx.modes = ADJ_OFFSET_SS_READ;
+	TEST_RES(adjtimex(&tx), tx.offset > 0 && tx.offset <= 10000);
+
+	// Cancelling the offset reports the remaining one.
+	tx.modes = ADJ_OFFSET_SINGLESHOT;
+	tx.offset = 0;
+	TEST_RES(adjtimex(&tx), tx.offset > 0 && tx.offset <= 10000);
+	tx.modes = ADJ_OFFSET_SS_READ;
+	TEST_RES(adjtimex(&tx), tx.offset == 0);
+}
+END_TEST()
+
+FN_TEST(step)
+{
+	struct timex tx = { .modes = ADJ_SETOFFSET };
+	long long before = wall_offset_msecs();
+
+	tx.time.tv_sec = 10;
+	tx.time.tv_usec = 0;
+	TEST_SUCC(adjtimex(&tx));
+	TEST_RES(wall_offset_msecs() - before, _ret >= 9990 && _ret <= 10010);
+
+	tx.modes = ADJ_SETOFFSET | ADJ_NANO;
+	tx.time.tv_sec = -10;
+	tx.time.tv_usec = 0;
+	TEST_SUCC(adjtimex(&tx));
+	TEST_RES(wall_offset_msecs() - before, _ret >= -10 && _ret <= 10);
+
+	tx.modes = ADJ_MICRO;
+	TEST_RES(adjtimex(&tx), !(tx.status & STA_NANO));
+}
+END_TEST()
+
+FN_TEST(tai_offset)
+{
+	struct timex tx = { .modes = ADJ_TAI, .constant = 37 };
+
+	TEST_RES(adjtimex(&tx), tx.tai == 37);
+	TEST_RES((clock_nsecs(CLOCK_TAI) - clock_nsecs(CLOCK_REALTIME)) /
+			 1000000,
+		 _ret >= 36990 && _ret <= 37010);
+
+	tx.constant = 0;
+	TEST_RES(adjtimex(&tx), tx.tai == 0);
+}
+END_TEST()
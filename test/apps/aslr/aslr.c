@@ -0,0 +1,147 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include "../network/test.h"
+#include <fcntl.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define RANDOMIZE_VA_SPACE "/proc/sys/kernel/randomize_va_space"
+#define LAYOUT_PROGRAM "/test/aslr/layout"
+
+struct layout {
+	unsigned long stack;
+	unsigned long mmap;
+	unsigned long pie;
+	unsigned long brk;
+};
+
+static int write_level(const char *level)
+{
+	int fd, ret;
+
+	fd = open(RANDOMIZE_VA_SPACE, O_WRONLY);
+	if (fd < 0)
+		return -1;
+	ret = write(fd, level, strlen(level));
+	close(fd);
+	return ret;
+}
+
+static int read_level(void)
+{
+	char buf[8] = { 0 };
+	int fd, ret;
+
+	fd = open(RANDOMIZE_VA_SPACE, O_RDONLY);
+	if (fd < 0)
+		return -1;
+	ret = read(fd, buf, sizeof(buf) - 1);
+	close(fd);
+	if (ret != 2 || buf[1] != '\n')
+		return -1;
+	return buf[0] - '0';
+}
+
+// Executes the layout program and parses the addresses that it prints.
+static int get_layout(struct layout *layout)
+{
+	char buf[128] = { 0 };
+	int pipefd[2], status;
+	ssize_t len;
+	pid_t pid;
+
+	if (pipe(pipefd) < 0)
+		return -1;
+
+	pid = fork();
+	if (pid < 0)
+		return -1;
+	if (pid == 0) {
+		close(pipefd[0]);
+		if (dup2(pipefd[1], STDOUT_FILENO) < 0)
+			_exit(1);
+		execl(LAYOUT_PROGRAM, LAYOUT_PROGRAM, NULL);
+		_exit(1);
+	}
+
+	close(pipefd[1]);
+	len = read(pipefd[0], buf, sizeof(buf) - 1);
+	close(pipefd[0]);
+
+	if (waitpid(pid, &status, 0) != pid || !WIFEXITED(status) ||
+	    WEXITSTATUS(status) != 0 || len <= 0)
+		return -1;
+	if (sscanf(buf, "%lx %lx %lx %lx", &layout->stack, &layout->mmap,
+		   &layout->pie, &layout->brk) != 4)
+		return -1;
+	return 0;
+}
+
+static struct layout layout1, layout2;
+
+FN_TEST(default_level)
+{
+	TEST_RES(read_level(), _ret == 2);
+}
+END_TEST()
+
+FN_TEST(invalid_level)
+{
+	TEST_ERRNO(write_level("3"), EINVAL);
+	TEST_ERRNO(write_level("-1"), EINVAL);
+	TEST_ERRNO(write_level("full"), EINVAL);
+	TEST_RES(read_level(), _ret == 2);
+}
+END_TEST()
+
+FN_TEST(full_randomization)
+{
+	TEST_SUCC(get_layout(&layout1));
+	TEST_SUCC(get_layout(&layout2));
+
+	TEST_RES(0, layout1.stack != layout2.stack);
+	TEST_RES(0, layout1.mmap != layout2.mmap);
+	TEST_RES(0, layout1.pie != layout2.pie);
+	TEST_RES(0, layout1.brk != layout2.brk);
+}
+END_TEST()
+
+FN_TEST(conservative_randomization)
+{
+	TEST_RES(write_level("1\n"), _ret == 2);
+	TEST_RES(read_level(), _ret == 1);
+
+	TEST_SUCC(get_layout(&layout1));
+	TEST_SUCC(get_layout(&layout2));
+
+	TEST_RES(0, layout1.stack != layout2.stack);
+	TEST_RES(0, layout1.mmap != layout2.mmap);
+	TEST_RES(0, layout1.pie != layout2.pie);
+	TEST_RES(0, layout1.brk == layout2.brk);
+}
+END_TEST()
+
+FN_TEST(no_randomization)
+{
+	TEST_RES(write_level("0"), _ret == 1);
+	TEST_RES(read_level(), _ret == 0);
+
+	TEST_SUCC(get_layout(&layout1));
+	TEST_SUCC(get_layout(&layout2));
+
+	TEST_RES(0, layout1.stack == layout2.stack);
+	TEST_RES(0, layout1.mmap == layout2.mmap);
+	TEST_RES(0, layout1.pie == layout2.pie);
+	TEST_RES(0, layout1.brk == layout2.brk);
+}
+END_TEST()
+
+FN_SETUP(cleanup)
+{
+	CHECK(write_level("2"));
+}
+END_SETUP()
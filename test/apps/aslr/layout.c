@@ -0,0 +1,22 @@
+// SPDX-License-Identifier: MPL-2.0
+
+// Prints the addresses that are randomized by ASLR, which are read by `aslr`.
+
+#include <stdio.h>
+#include <sys/mman.h>
+#include <unistd.h>
+
+int main(void)
+{
+	int stack_var;
+	void *mmap_addr = mmap(NULL, 4096, PROT_READ | PROT_WRITE,
+			       MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+
+	if (mmap_addr == MAP_FAILED)
+		return 1;
+
+	printf("%lx %lx %lx %lx\n", (unsigned long)&stack_var,
+	       (unsigned long)mmap_addr, (unsigned long)(void *)&main,
+	       (unsigned long)sbrk(0));
+	return 0;
+}
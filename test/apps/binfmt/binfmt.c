@@ -0,0 +1,243 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include "../network/test.h"
+#include <errno.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/stat.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define PRINT_ARGS "/test/binfmt/print_args"
+#define BINFMT_MISC "/proc/sys/fs/binfmt_misc"
+
+static char output[1024];
+
+static int write_file(const char *path, const char *content, mode_t mode)
+{
+	int fd, ret;
+
+	fd = open(path, O_WRONLY | O_CREAT | O_TRUNC, mode);
+	if (fd < 0)
+		return -1;
+	ret = write(fd, content, strlen(content));
+	close(fd);
+	return ret;
+}
+
+static int read_file(const char *path)
+{
+	int fd, ret;
+
+	memset(output, 0, sizeof(output));
+	fd = open(path, O_RDONLY);
+	if (fd < 0)
+		return -1;
+	ret = read(fd, output, sizeof(output) - 1);
+	close(fd);
+	return ret;
+}
+
+// Executes the file and collects what it prints in `output`. Returns the exit code of the
+// child, which is the error number if the file cannot be executed.
+static int run(const char *path, char *const argv[])
+{
+	int pipefd[2], status;
+	ssize_t len;
+	pid_t pid;
+
+	memset(output, 0, sizeof(output));
+	if (pipe(pipefd) < 0)
+		return -1;
+
+	pid = fork();
+	if (pid < 0)
+		return -1;
+	if (pid == 0) {
+		close(pipefd[0]);
+		if (dup2(pipefd[1], STDOUT_FILENO) < 0)
+			_exit(255);
+		execv(path, argv);
+		_exit(errno);
+	}
+
+	close(pipefd[1]);
+	len = read(pipefd[0], output, sizeof(output) - 1);
+	close(pipefd[0]);
+	if (len < 0)
+		return -1;
+
+	if (waitpid(pid, &status, 0) != pid || !WIFEXITED(status))
+		return -1;
+	return WEXITSTATUS(status);
+}
+
+FN_TEST(shebang_argument)
+{
+	char *argv[] = { "argv0", "arg1", NULL };
+
+	// Everything after the interpreter is passed as one argument.
+	TEST_SUCC(write_file("/tmp/binfmt_script",
+			     "#! " PRINT_ARGS "  -x  y z \t\necho\n", 0755));
+	TEST_RES(run("/tmp/binfmt_script", argv),
+		 _ret == 0 && strcmp(output, PRINT_ARGS "\n-x  y z\n"
+					     "/tmp/binfmt_script\narg1\n") == 0);
+
+	TEST_SUCC(write_file("/tmp/binfmt_script", "#!" PRINT_ARGS "\n", 0755));
+	TEST_RES(run("/tmp/binfmt_script", argv),
+		 _ret == 0 && strcmp(output, PRINT_ARGS
+				     "\n/tmp/binfmt_script\narg1\n") == 0);
+
+	TEST_SUCC(write_file("/tmp/binfmt_script", "#! \t\n", 0755));
+	TEST_RES(run("/tmp/binfmt_script", argv), _ret == ENOEXEC);
+
+	TEST_SUCC(unlink("/tmp/binfmt_script"));
+}
+END_TEST()
+
+// Creates `/tmp/binfmt_script[i]` for `i` in `[0, n)`, each of which is interpreted by the next
+// one, and the last one is interpreted by `print_args`.
+static int create_scripts(int n)
+{
+	char path[64], content[64];
+	int i;
+
+	for (i = 0; i < n; ++i) {
+		sprintf(path, "/tmp/binfmt_script%d", i);
+		if (i == n - 1)
+			sprintf(content, "#!%s\n", PRINT_ARGS);
+		else
+			sprintf(content, "#!/tmp/binfmt_script%d\n", i + 1);
+		if (write_file(path, content, 0755) < 0)
+			return -1;
+	}
+	return 0;
+}
+
+static int remove_scripts(int n)
+{
+	char path[64];
+	int i;
+
+	for (i = 0; i < n; ++i) {
+		sprintf(path, "/tmp/binfmt_script%d", i);
+		if (unlink(path) < 0)
+			return -1;
+	}
+	return 0;
+}
+
+FN_TEST(shebang_recursion)
+{
+	char *argv[] = { "argv0", NULL };
+
+	// Four levels of interpreters are allowed.
+	TEST_SUCC(create_scripts(4));
+	TEST_RES(run("/tmp/binfmt_script0", argv),
+		 _ret == 0 && strcmp(output, PRINT_ARGS
+				     "\n/tmp/binfmt_script3\n") == 0);
+	TEST_SUCC(remove_scripts(4));
+
+	TEST_SUCC(create_scripts(8));
+	TEST_RES(run("/tmp/binfmt_script0", argv), _ret == ELOOP);
+	TEST_SUCC(remove_scripts(8));
+}
+END_TEST()
+
+FN_TEST(misc_extension)
+{
+	char *argv[] = { "argv0", "arg1", NULL };
+
+	TEST_RES(read_file(BINFMT_MISC "/status"),
+		 strcmp(output, "enabled\n") == 0);
+
+	TEST_SUCC(write_file("/tmp/binfmt.test", "not a program\n", 0755));
+	TEST_RES(run("/tmp/binfmt.test", argv), _ret == ENOEXEC);
+
+	TEST_RES(write_file(BINFMT_MISC "/register",
+			    ":test_ext:E::test::" PRINT_ARGS ":\n", 0),
+		 _ret > 0);
+	TEST_ERRNO(write_file(BINFMT_MISC "/register",
+			      ":test_ext:E::test::" PRINT_ARGS ":\n", 0),
+		   EEXIST);
+	TEST_RES(read_file(BINFMT_MISC "/test_ext"),
+		 strcmp(output, "enabled\ninterpreter " PRINT_ARGS
+				"\nflags: \nextension .test\n") == 0);
+
+	// The original `argv[0]` is replaced by the path of the file.
+	TEST_RES(run("/tmp/binfmt.test", argv),
+		 _ret == 0 && strcmp(output, PRINT_ARGS
+				     "\n/tmp/binfmt.test\narg1\n") == 0);
+
+	TEST_RES(write_file(BINFMT_MISC "/test_ext", "0", 0), _ret == 1);
+	TEST_RES(read_file(BINFMT_MISC "/test_ext"),
+		 strncmp(output, "disabled\n", 9) == 0);
+	TEST_RES(run("/tmp/binfmt.test", argv), _ret == ENOEXEC);
+
+	TEST_RES(write_file(BINFMT_MISC "/test_ext", "-1", 0), _ret == 2);
+	TEST_ERRNO(open(BINFMT_MISC "/test_ext", O_RDONLY), ENOENT);
+
+	TEST_SUCC(unlink("/tmp/binfmt.test"));
+}
+END_TEST()
+
+FN_TEST(misc_magic)
+{
+	char *argv[] = { "argv0", "arg1", NULL };
+
+	TEST_RES(write_file(BINFMT_MISC "/register",
+			    ":test_magic:M:2:\\x41\\x5a:\\xff\\xdf:" PRINT_ARGS
+			    ":P\n",
+			    0),
+		 _ret > 0);
+	TEST_RES(read_file(BINFMT_MISC "/test_magic"),
+		 strcmp(output, "enabled\ninterpreter " PRINT_ARGS
+				"\nflags: P\noffset 2\nmagic 415a\nmask ffdf\n") ==
+			 0);
+
+	// The mask makes the second byte case-insensitive. The original `argv[0]` is preserved.
+	TEST_SUCC(write_file("/tmp/binfmt_magic", "..Az\n", 0755));
+	TEST_RES(run("/tmp/binfmt_magic", argv),
+		 _ret == 0 && strcmp(output, PRINT_ARGS
+				     "\n/tmp/binfmt_magic\nargv0\narg1\n") == 0);
+
+	TEST_SUCC(write_file("/tmp/binfmt_magic", ".AZ\n", 0755));
+	TEST_RES(run("/tmp/binfmt_magic", argv), _ret == ENOEXEC);
+
+	// The handlers are not used when `binfmt_misc` is disabled.
+	TEST_SUCC(write_file("/tmp/binfmt_magic", "..AZ\n", 0755));
+	TEST_RES(write_file(BINFMT_MISC "/status", "0\n", 0), _ret == 2);
+	TEST_RES(run("/tmp/binfmt_magic", argv), _ret == ENOEXEC);
+	TEST_RES(write_file(BINFMT_MISC "/status", "1\n", 0), _ret == 2);
+	TEST_RES(run("/tmp/binfmt_magic", argv), _ret == 0);
+
+	// All the handlers are removed.
+	TEST_RES(write_file(BINFMT_MISC "/status", "-1\n", 0), _ret == 3);
+	TEST_ERRNO(open(BINFMT_MISC "/test_magic", O_RDONLY), ENOENT);
+	TEST_RES(run("/tmp/binfmt_magic", argv), _ret == ENOEXEC);
+
+	TEST_SUCC(unlink("/tmp/binfmt_magic"));
+}
+END_TEST()
+
+FN_TEST(misc_invalid)
+{
+	TEST_ERRNO(write_file(BINFMT_MISC "/register",
+			      ":test:X::magic::" PRINT_ARGS ":\n", 0),
+		   EINVAL);
+	TEST_ERRNO(write_file(BINFMT_MISC "/register",
+			      ":test:M::\\x41:\\xff\\xff:" PRINT_ARGS ":\n", 0),
+		   EINVAL);
+	TEST_ERRNO(write_file(BINFMT_MISC "/register",
+			      ":test:E:1:ext::" PRINT_ARGS ":\n", 0),
+		   EINVAL);
+	TEST_ERRNO(write_file(BINFMT_MISC "/register", ":test:M::AB:::\n", 0),
+		   EINVAL);
+	TEST_ERRNO(write_file(BINFMT_MISC "/register",
+			      ":../test:M::AB::" PRINT_ARGS ":\n", 0),
+		   EINVAL);
+}
+END_TEST()
@@ -0,0 +1,14 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#include <stdio.h>
+
+// Prints the arguments line by line, so the arguments that are passed by the kernel to an
+// interpreter can be checked.
+int main(int argc, char *argv[])
+{
+	int i;
+
+	for (i = 0; i < argc; ++i)
+		printf("%s\n", argv[i]);
+	return 0;
+}
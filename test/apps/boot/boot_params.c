@@ -0,0 +1,79 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include "../network/test.h"
+#include <fcntl.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+
+static char params[4096];
+
+FN_SETUP(read_params)
+{
+	int fd;
+	ssize_t len;
+
+	fd = CHECK(open("/proc/boot_params", O_RDONLY));
+	len = CHECK_WITH(read(fd, params, sizeof(params) - 1),
+			 _ret > 0 && _ret < sizeof(params) - 1);
+	params[len] = '\0';
+	CHECK(close(fd));
+}
+END_SETUP()
+
+// Returns the value of the parameter, or -1 if it is absent or not a number.
+static long long param_value(const char *name)
+{
+	char pattern[64];
+	const char *pos;
+	char *end;
+	long long value;
+
+	strcpy(pattern, "\n");
+	strcat(pattern, name);
+	strcat(pattern, "=");
+	if (strncmp(params, pattern + 1, strlen(pattern + 1)) == 0)
+		pos = params + strlen(pattern + 1);
+	else if ((pos = strstr(params, pattern)) != NULL)
+		pos += strlen(pattern);
+	else
+		return -1;
+
+	value = strtoll(pos, &end, 0);
+	if (end == pos || *end != '\n')
+		return -1;
+	return value;
+}
+
+FN_TEST(sched_params)
+{
+	TEST_RES(param_value("sched.base_slice_ns"), _ret > 0);
+	TEST_RES(param_value("sched.latency_ns"), _ret > 0);
+	TEST_RES(param_value("sched.no_such_param"), _ret == -1);
+}
+END_TEST()
+
+FN_TEST(sorted_lines)
+{
+	char *line, *next, *eq, *prev = NULL;
+	int sorted = 1;
+
+	// Each line is `module.name=value`, and the names are in order.
+	for (line = params; *line != '\0'; line = next + 1) {
+		next = strchr(line, '\n');
+		eq = strchr(line, '=');
+		if (next == NULL || eq == NULL || eq > next) {
+			sorted = 0;
+			break;
+		}
+		*next = '\0';
+		*eq = '\0';
+		if (prev != NULL && strcmp(prev, line) >= 0)
+			sorted = 0;
+		prev = line;
+	}
+	TEST_RES(sorted, _ret == 1);
+}
+END_TEST()
@@ -0,0 +1,321 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include "../network/test.h"
+#include <fcntl.h>
+#include <linux/genetlink.h>
+#include <linux/netlink.h>
+#include <linux/taskstats.h>
+#include <stddef.h>
+#include <string.h>
+#include <sys/resource.h>
+#include <sys/socket.h>
+#include <sys/times.h>
+#include <sys/wait.h>
+#include <time.h>
+#include <unistd.h>
+
+#define SPIN_MSECS 200
+// The threshold is lower than the spinning time because the test may be preempted.
+#define MIN_CPU_MSECS 50
+#define USER_HZ 100
+
+#ifndef RUSAGE_BOTH
+#define RUSAGE_BOTH (-2)
+#endif
+
+static long timeval_to_msecs(struct timeval *tv)
+{
+	return tv->tv_sec * 1000 + tv->tv_usec / 1000;
+}
+
+static long timespec_to_msecs(struct timespec *ts)
+{
+	return ts->tv_sec * 1000 + ts->tv_nsec / 1000000;
+}
+
+// Spins in the user space for `SPIN_MSECS` milliseconds of the wall-clock time.
+static void spin(void)
+{
+	static volatile unsigned long counter;
+	struct timespec start, now;
+	int i;
+
+	clock_gettime(CLOCK_MONOTONIC, &start);
+	do {
+		for (i = 0; i < 1000000; ++i)
+			++counter;
+		clock_gettime(CLOCK_MONOTONIC, &now);
+	} while (timespec_to_msecs(&now) - timespec_to_msecs(&start) <
+		 SPIN_MSECS);
+}
+
+FN_TEST(self_time)
+{
+	struct rusage usage;
+	struct timespec ts;
+	struct tms tms;
+
+	spin();
+
+	TEST_RES(getrusage(RUSAGE_SELF, &usage),
+		 timeval_to_msecs(&usage.ru_utime) >= MIN_CPU_MSECS);
+	TEST_RES(getrusage(RUSAGE_THREAD, &usage),
+		 timeval_to_msecs(&usage.ru_utime) >= MIN_CPU_MSECS);
+	TEST_RES(clock_gettime(CLOCK_PROCESS_CPUTIME_ID, &ts),
+		 timespec_to_msecs(&ts) >= MIN_CPU_MSECS);
+	TEST_RES(clock_gettime(CLOCK_THREAD_CPUTIME_ID, &ts),
+		 timespec_to_msecs(&ts) >= MIN_CPU_MSECS);
+	TEST_RES(times(&tms), _ret > 0 && tms.tms_utime >=
+						  MIN_CPU_MSECS * USER_HZ / 1000);
+}
+END_TEST()
+
+FN_TEST(children_time)
+{
+	struct rusage usage, children_usage;
+	struct tms tms;
+	int status;
+	pid_t pid;
+
+	TEST_SUCC(getrusage(RUSAGE_CHILDREN, &children_usage));
+
+	pid = TEST_SUCC(fork());
+	if (pid == 0) {
+		spin();
+		_exit(0);
+	}
+
+	TEST_RES(wait4(pid, &status, 0, &usage),
+		 _ret == pid && timeval_to_msecs(&usage.ru_utime) >=
+					MIN_CPU_MSECS);
+	TEST_RES(getrusage(RUSAGE_CHILDREN, &usage),
+		 timeval_to_msecs(&usage.ru_utime) -
+				 timeval_to_msecs(&children_usage.ru_utime) >=
+			 MIN_CPU_MSECS);
+	TEST_RES(times(&tms), tms.tms_cutime >= MIN_CPU_MSECS * USER_HZ / 1000);
+
+	// `RUSAGE_BOTH` is only used internally in Linux.
+	TEST_ERRNO(getrusage(RUSAGE_BOTH, &usage), EINVAL);
+}
+END_TEST()
+
+static char buf[4096];
+
+static int read_file(const char *path)
+{
+	int fd, ret;
+
+	memset(buf, 0, sizeof(buf));
+	fd = open(path, O_RDONLY);
+	if (fd < 0)
+		return -1;
+	ret = read(fd, buf, sizeof(buf) - 1);
+	close(fd);
+	return ret;
+}
+
+// Returns the number of fields after the command name in `/proc/[pid]/stat`.
+static int count_stat_fields(void)
+{
+	char *fields = strrchr(buf, ')') + 1;
+	int nr_fields = 0;
+
+	for (fields = strtok(fields, " \n"); fields != NULL;
+	     fields = strtok(NULL, " \n"))
+		++nr_fields;
+	return nr_fields;
+}
+
+// Returns the `utime` field, which is the 14th field, in `/proc/[pid]/stat`.
+static long parse_stat_utime(void)
+{
+	long utime;
+
+	if (sscanf(strrchr(buf, ')') + 2,
+		   "%*c %*d %*d %*d %*d %*d %*u %*u %*u %*u %*u %ld",
+		   &utime) != 1)
+		return -1;
+	return utime;
+}
+
+FN_TEST(procfs)
+{
+	TEST_RES(read_file("/proc/stat"),
+		 _ret > 0 && strncmp(buf, "cpu ", 4) == 0);
+
+	TEST_RES(read_file("/proc/self/stat"), _ret > 0);
+	TEST_RES(parse_stat_utime(), _ret >= MIN_CPU_MSECS * USER_HZ / 1000);
+	// There are 52 fields, including the PID and the command name.
+	TEST_RES(count_stat_fields(), _ret == 50);
+}
+END_TEST()
+
+static int sk_genl;
+static unsigned int seq;
+static char msg[4096];
+
+FN_SETUP(genl_socket)
+{
+	sk_genl = CHECK(socket(AF_NETLINK, SOCK_RAW, NETLINK_GENERIC));
+}
+END_SETUP()
+
+static struct nlmsghdr *init_genl_request(int type, int cmd)
+{
+	struct nlmsghdr *nlh = (struct nlmsghdr *)msg;
+	struct genlmsghdr *genl = NLMSG_DATA(nlh);
+
+	memset(msg, 0, sizeof(msg));
+	nlh->nlmsg_len = NLMSG_LENGTH(GENL_HDRLEN);
+	nlh->nlmsg_type = type;
+	nlh->nlmsg_flags = NLM_F_REQUEST;
+	nlh->nlmsg_seq = ++seq;
+	genl->cmd = cmd;
+	genl->version = 1;
+
+	return nlh;
+}
+
+static void add_attr(struct nlmsghdr *nlh, int type, const void *data,
+		     size_t len)
+{
+	struct nlattr *nla =
+		(struct nlattr *)((char *)nlh + NLMSG_ALIGN(nlh->nlmsg_len));
+
+	nla->nla_type = type;
+	nla->nla_len = NLA_HDRLEN + len;
+	memcpy((char *)nla + NLA_HDRLEN, data, len);
+	nlh->nlmsg_len = NLMSG_ALIGN(nlh->nlmsg_len) + NLA_ALIGN(nla->nla_len);
+}
+
+/*
+ * Sends the request and receives the reply in `msg`. Returns the length of the
+ * attributes in the reply, or -1 if the reply is not of the request type.
+ */
+static int send_request(struct nlmsghdr *nlh)
+{
+	int type = nlh->nlmsg_type;
+
+	if (send(sk_genl, nlh, nlh->nlmsg_len, 0) < 0)
+		return -1;
+	if (recv(sk_genl, msg, sizeof(msg), 0) < 0)
+		return -1;
+	if (nlh->nlmsg_type != type)
+		return -1;
+
+	return nlh->nlmsg_len - NLMSG_LENGTH(GENL_HDRLEN);
+}
+
+static struct nlattr *reply_attrs(void)
+{
+	return (struct nlattr *)((char *)NLMSG_DATA(msg) + GENL_HDRLEN);
+}
+
+static struct nlattr *find_attr(struct nlattr *nla, int len, int type)
+{
+	while (len >= NLA_HDRLEN && nla->nla_len >= NLA_HDRLEN &&
+	       nla->nla_len <= len) {
+		if ((nla->nla_type & NLA_TYPE_MASK) == type)
+			return nla;
+		len -= NLA_ALIGN(nla->nla_len);
+		nla = (struct nlattr *)((char *)nla + NLA_ALIGN(nla->nla_len));
+	}
+	return NULL;
+}
+
+#define NLA_DATA(nla) ((void *)((char *)(nla) + NLA_HDRLEN))
+#define NLA_PAYLOAD_LEN(nla) ((nla)->nla_len - NLA_HDRLEN)
+
+static int family_id;
+
+FN_TEST(taskstats_family)
+{
+	struct nlmsghdr *nlh = init_genl_request(GENL_ID_CTRL, CTRL_CMD_GETFAMILY);
+	struct nlattr *nla;
+	int len;
+
+	add_attr(nlh, CTRL_ATTR_FAMILY_NAME, TASKSTATS_GENL_NAME,
+		 strlen(TASKSTATS_GENL_NAME) + 1);
+	len = TEST_RES(send_request(nlh), _ret > 0);
+
+	nla = find_attr(reply_attrs(), len, CTRL_ATTR_FAMILY_ID);
+	TEST_RES(nla != NULL, _ret);
+	family_id = *(unsigned short *)NLA_DATA(nla);
+}
+END_TEST()
+
+/*
+ * Queries the statistics of the thread or the process. Returns NULL if the reply
+ * does not contain the statistics of the specified thread or process.
+ */
+static struct taskstats *query_taskstats(int cmd_attr, unsigned int id,
+					 int aggr_type, int id_type)
+{
+	struct nlmsghdr *nlh = init_genl_request(family_id, TASKSTATS_CMD_GET);
+	struct nlattr *aggr, *nla;
+	int len;
+
+	add_attr(nlh, cmd_attr, &id, sizeof(id));
+	len = send_request(nlh);
+	if (len < 0)
+		return NULL;
+
+	aggr = find_attr(reply_attrs(), len, aggr_type);
+	if (aggr == NULL)
+		return NULL;
+
+	nla = find_attr(NLA_DATA(aggr), NLA_PAYLOAD_LEN(aggr), id_type);
+	if (nla == NULL || *(unsigned int *)NLA_DATA(nla) != id)
+		return NULL;
+
+	nla = find_attr(NLA_DATA(aggr), NLA_PAYLOAD_LEN(aggr),
+			TASKSTATS_TYPE_STATS);
+	if (nla == NULL ||
+	    NLA_PAYLOAD_LEN(nla) < offsetof(struct taskstats, ac_minflt))
+		return NULL;
+
+	return NLA_DATA(nla);
+}
+
+FN_TEST(taskstats_pid)
+{
+	struct taskstats *stats;
+
+	stats = query_taskstats(TASKSTATS_CMD_ATTR_PID, getpid(),
+				TASKSTATS_TYPE_AGGR_PID, TASKSTATS_TYPE_PID);
+	TEST_RES(stats != NULL, _ret);
+	TEST_RES(stats->ac_pid, _ret == getpid());
+	TEST_RES(stats->ac_ppid, _ret == getppid());
+	TEST_RES(stats->ac_uid, _ret == getuid());
+	TEST_RES(stats->ac_utime, _ret >= MIN_CPU_MSECS * 1000);
+}
+END_TEST()
+
+FN_TEST(taskstats_tgid)
+{
+	struct taskstats *stats;
+
+	stats = query_taskstats(TASKSTATS_CMD_ATTR_TGID, getpid(),
+				TASKSTATS_TYPE_AGGR_TGID, TASKSTATS_TYPE_TGID);
+	TEST_RES(stats != NULL, _ret);
+	TEST_RES(stats->ac_utime, _ret >= MIN_CPU_MSECS * 1000);
+	TEST_RES(stats->cpu_run_real_total,
+		 _ret / 1000 >= stats->ac_utime + stats->ac_stime - 1);
+}
+END_TEST()
+
+FN_TEST(taskstats_invalid)
+{
+	struct nlmsghdr *nlh = init_genl_request(family_id, TASKSTATS_CMD_GET);
+	unsigned int pid = 0x7fffffff;
+
+	add_attr(nlh, TASKSTATS_CMD_ATTR_TGID, &pid, sizeof(pid));
+	nlh->nlmsg_flags |= NLM_F_ACK;
+	TEST_RES(send(sk_genl, nlh, nlh->nlmsg_len, 0), _ret > 0);
+	TEST_RES(recv(sk_genl, msg, sizeof(msg), 0),
+		 _ret > 0 && nlh->nlmsg_type == NLMSG_ERROR &&
+			 ((struct nlmsgerr *)NLMSG_DATA(nlh))->error == -ESRCH);
+}
+END_TEST()
@@ -0,0 +1,152 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#include "../network/test.h"
+#include <fcntl.h>
+#include <linux/input.h>
+#include <string.h>
+#include <sys/ioctl.h>
+#include <time.h>
+#include <unistd.h>
+
+#define EVDEV_PATH "/dev/input/event0"
+
+#define BIT_IS_SET(bits, bit) (((bits)[(bit) / 8] >> ((bit) % 8)) & 1)
+
+static int fd1;
+static int fd2;
+static char name[256];
+static unsigned char bits[KEY_MAX / 8 + 1];
+static struct input_event events[4];
+
+static int write_event(int fd, unsigned short type, unsigned short code,
+		       int value)
+{
+	struct input_event event = {
+		.type = type,
+		.code = code,
+		.value = value,
+	};
+
+	return write(fd, &event, sizeof(event));
+}
+
+FN_SETUP(open)
+{
+	fd1 = CHECK(open(EVDEV_PATH, O_RDWR | O_NONBLOCK));
+	fd2 = CHECK(open(EVDEV_PATH, O_RDWR | O_NONBLOCK));
+}
+END_SETUP()
+
+FN_TEST(query)
+{
+	int version;
+	struct input_id id;
+
+	TEST_RES(ioctl(fd1, EVIOCGVERSION, &version), version == EV_VERSION);
+	TEST_SUCC(ioctl(fd1, EVIOCGID, &id));
+	TEST_RES(ioctl(fd1, EVIOCGNAME(sizeof(name)), name),
+		 _ret > 1 && _ret == strlen(name) + 1);
+	TEST_RES(ioctl(fd1, EVIOCGNAME(4), name), _ret == 4);
+
+	memset(bits, 0, sizeof(bits));
+	TEST_RES(ioctl(fd1, EVIOCGBIT(0, sizeof(bits)), bits),
+		 _ret == 8 && BIT_IS_SET(bits, EV_SYN) &&
+			 BIT_IS_SET(bits, EV_KEY));
+	TEST_RES(ioctl(fd1, EVIOCGBIT(EV_KEY, sizeof(bits)), bits),
+		 _ret == sizeof(bits) && BIT_IS_SET(bits, KEY_A) &&
+			 BIT_IS_SET(bits, KEY_ENTER));
+	TEST_RES(ioctl(fd1, EVIOCGBIT(EV_KEY, 1), bits), _ret == 1);
+	TEST_RES(ioctl(fd1, EVIOCGKEY(sizeof(bits)), bits),
+		 _ret == sizeof(bits) && !BIT_IS_SET(bits, KEY_A));
+	TEST_RES(ioctl(fd1, EVIOCGLED(sizeof(bits)), bits), _ret == 8);
+	TEST_ERRNO(ioctl(fd1, EVIOCGBIT(EV_REP, sizeof(bits)), bits), EINVAL);
+	TEST_ERRNO(ioctl(fd1, EVIOCGPHYS(sizeof(name)), name), ENOENT);
+}
+END_TEST()
+
+FN_TEST(inject_and_read)
+{
+	TEST_ERRNO(read(fd1, events, sizeof(events)), EAGAIN);
+	TEST_ERRNO(read(fd1, events, sizeof(events[0]) - 1), EINVAL);
+	TEST_ERRNO(write(fd1, events, sizeof(events[0]) - 1), EINVAL);
+
+	// The events are not readable until a `SYN_REPORT` arrives.
+	TEST_RES(write_event(fd1, EV_KEY, KEY_A, 1), _ret == sizeof(events[0]));
+	TEST_ERRNO(read(fd2, events, sizeof(events)), EAGAIN);
+	TEST_RES(write_event(fd1, EV_SYN, SYN_REPORT, 0),
+		 _ret == sizeof(events[0]));
+
+	TEST_RES(read(fd2, events, sizeof(events)),
+		 _ret == 2 * sizeof(events[0]) && events[0].type == EV_KEY &&
+			 events[0].code == KEY_A && events[0].value == 1 &&
+			 events[1].type == EV_SYN &&
+			 events[1].code == SYN_REPORT);
+	TEST_RES(read(fd1, events, sizeof(events[0])),
+		 _ret == sizeof(events[0]) && events[0].code == KEY_A);
+	TEST_RES(read(fd1, events, sizeof(events)),
+		 _ret == sizeof(events[0]) && events[0].type == EV_SYN);
+	TEST_RES(ioctl(fd1, EVIOCGKEY(sizeof(bits)), bits),
+		 BIT_IS_SET(bits, KEY_A));
+
+	// The events that the device cannot report are ignored.
+	TEST_RES(write_event(fd1, EV_ABS, ABS_X, 1), _ret == sizeof(events[0]));
+
+	TEST_SUCC(write_event(fd1, EV_KEY, KEY_A, 0));
+	TEST_SUCC(write_event(fd1, EV_SYN, SYN_REPORT, 0));
+	TEST_RES(read(fd1, events, sizeof(events)),
+		 _ret == 2 * sizeof(events[0]) && events[0].code == KEY_A &&
+			 events[0].value == 0);
+	TEST_RES(read(fd2, events, sizeof(events)),
+		 _ret == 2 * sizeof(events[0]));
+	TEST_RES(ioctl(fd1, EVIOCGKEY(sizeof(bits)), bits),
+		 !BIT_IS_SET(bits, KEY_A));
+}
+END_TEST()
+
+FN_TEST(grab)
+{
+	TEST_ERRNO(ioctl(fd1, EVIOCGRAB, 0), EINVAL);
+	TEST_SUCC(ioctl(fd1, EVIOCGRAB, 1));
+	TEST_ERRNO(ioctl(fd2, EVIOCGRAB, 1), EBUSY);
+	TEST_ERRNO(ioctl(fd2, EVIOCGRAB, 0), EINVAL);
+
+	// Only the grabbing file receives the events.
+	TEST_SUCC(write_event(fd2, EV_SYN, SYN_REPORT, 0));
+	TEST_RES(read(fd1, events, sizeof(events)),
+		 _ret == sizeof(events[0]));
+	TEST_ERRNO(read(fd2, events, sizeof(events)), EAGAIN);
+
+	TEST_SUCC(ioctl(fd1, EVIOCGRAB, 0));
+	TEST_SUCC(write_event(fd2, EV_SYN, SYN_REPORT, 0));
+	TEST_RES(read(fd1, events, sizeof(events)),
+		 _ret == sizeof(events[0]));
+	TEST_RES(read(fd2, events, sizeof(events)),
+		 _ret == sizeof(events[0]));
+}
+END_TEST()
+
+FN_TEST(clock)
+{
+	int clock_id = CLOCK_MONOTONIC;
+	struct timespec now;
+
+	TEST_SUCC(ioctl(fd1, EVIOCSCLOCKID, &clock_id));
+	TEST_SUCC(write_event(fd1, EV_SYN, SYN_REPORT, 0));
+	TEST_SUCC(clock_gettime(CLOCK_MONOTONIC, &now));
+	TEST_RES(read(fd1, events, sizeof(events)),
+		 _ret == sizeof(events[0]) &&
+			 events[0].input_event_sec <= now.tv_sec);
+	TEST_RES(read(fd2, events, sizeof(events)),
+		 _ret == sizeof(events[0]));
+
+	clock_id = CLOCK_PROCESS_CPUTIME_ID;
+	TEST_ERRNO(ioctl(fd1, EVIOCSCLOCKID, &clock_id), EINVAL);
+}
+END_TEST()
+
+FN_SETUP(close)
+{
+	CHECK(close(fd1));
+	CHECK(close(fd2));
+}
+END_SETUP()
@@ -0,0 +1,163 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include "../network/test.h"
+#include <fcntl.h>
+#include <string.h>
+#include <sys/mount.h>
+#include <sys/stat.h>
+#include <unistd.h>
+
+// The flash is emulated by memory, whose size is given by `mtdram.total_size=`.
+#define DEVICE "mtd:mtdram test device"
+#define MNT "/flashfs_test"
+
+#define GC_FILE_SIZE (64 * 1024)
+#define GC_ROUNDS 32
+#define BIG_FILE_SIZE (1024 * 1024)
+
+static char gc_data[GC_FILE_SIZE];
+static char big_data[BIG_FILE_SIZE];
+static char buf[GC_FILE_SIZE];
+
+static int read_file(const char *path, char *out, size_t len)
+{
+	int fd = open(path, O_RDONLY);
+	if (fd < 0)
+		return -1;
+	ssize_t ret = read(fd, out, len);
+	close(fd);
+	return ret;
+}
+
+static int write_file(const char *path, const char *data, size_t len)
+{
+	int fd = open(path, O_WRONLY | O_CREAT | O_TRUNC, 0644);
+	if (fd < 0)
+		return -1;
+	ssize_t ret = write(fd, data, len);
+	close(fd);
+	return ret;
+}
+
+// Overwrites the file for multiple rounds, so the records exceed the flash.
+static int overwrite_rounds(const char *path)
+{
+	int fd = open(path, O_WRONLY | O_CREAT, 0644);
+	if (fd < 0)
+		return -1;
+	for (int round = 0; round < GC_ROUNDS; round++) {
+		memset(gc_data, 'a' + round % 26, GC_FILE_SIZE);
+		if (pwrite(fd, gc_data, GC_FILE_SIZE, 0) != GC_FILE_SIZE) {
+			close(fd);
+			return -1;
+		}
+	}
+	return close(fd);
+}
+
+FN_SETUP(mount)
+{
+	CHECK_WITH(mkdir(MNT, 0755), _ret == 0 || errno == EEXIST);
+	CHECK(mount(DEVICE, MNT, "flashfs", 0, NULL));
+}
+END_SETUP()
+
+FN_TEST(proc_mtd)
+{
+	char mtd[512] = { 0 };
+
+	TEST_RES(read_file("/proc/mtd", mtd, sizeof(mtd) - 1),
+		 strstr(mtd, "\"mtdram test device\"") != NULL);
+}
+END_TEST()
+
+FN_TEST(operations)
+{
+	TEST_SUCC(mkdir(MNT "/dir", 0755));
+	TEST_ERRNO(mkdir(MNT "/dir", 0755), EEXIST);
+	TEST_RES(write_file(MNT "/dir/config", "hello", 5), _ret == 5);
+	TEST_SUCC(chmod(MNT "/dir/config", 0600));
+	TEST_SUCC(symlink("dir/renamed", MNT "/link"));
+	TEST_SUCC(rename(MNT "/dir/config", MNT "/dir/renamed"));
+	TEST_RES(write_file(MNT "/removed", "bye", 3), _ret == 3);
+	TEST_SUCC(unlink(MNT "/removed"));
+
+	TEST_ERRNO(link(MNT "/dir/renamed", MNT "/hardlink"), EPERM);
+	TEST_ERRNO(rmdir(MNT "/dir"), ENOTEMPTY);
+	TEST_ERRNO(rename(MNT "/dir", MNT "/dir/sub"), EINVAL);
+}
+END_TEST()
+
+FN_SETUP(remount)
+{
+	CHECK(umount(MNT));
+	CHECK(mount(DEVICE, MNT, "flashfs", 0, NULL));
+}
+END_SETUP()
+
+FN_TEST(persisted)
+{
+	struct stat st;
+	char target[32] = { 0 };
+
+	memset(buf, 0, sizeof(buf));
+	TEST_RES(read_file(MNT "/dir/renamed", buf, sizeof(buf)),
+		 _ret == 5 && memcmp(buf, "hello", 5) == 0);
+	TEST_RES(stat(MNT "/dir/renamed", &st),
+		 (st.st_mode & 0777) == 0600 && st.st_size == 5);
+	TEST_RES(readlink(MNT "/link", target, sizeof(target)),
+		 _ret == 11 && memcmp(target, "dir/renamed", 11) == 0);
+	TEST_ERRNO(access(MNT "/dir/config", F_OK), ENOENT);
+	TEST_ERRNO(access(MNT "/removed", F_OK), ENOENT);
+}
+END_TEST()
+
+FN_TEST(garbage_collection)
+{
+	TEST_SUCC(overwrite_rounds(MNT "/gc"));
+	TEST_RES(read_file(MNT "/gc", buf, sizeof(buf)),
+		 _ret == GC_FILE_SIZE && memcmp(buf, gc_data, GC_FILE_SIZE) == 0);
+}
+END_TEST()
+
+FN_TEST(no_space)
+{
+	int fd;
+
+	fd = TEST_SUCC(open(MNT "/big", O_WRONLY | O_CREAT, 0644));
+	TEST_ERRNO(write(fd, big_data, BIG_FILE_SIZE), ENOSPC);
+	TEST_SUCC(close(fd));
+	TEST_SUCC(unlink(MNT "/big"));
+}
+END_TEST()
+
+FN_SETUP(remount_again)
+{
+	CHECK(umount(MNT));
+	CHECK(mount(DEVICE, MNT, "flashfs", 0, NULL));
+}
+END_SETUP()
+
+FN_TEST(persisted_after_gc)
+{
+	memset(buf, 0, sizeof(buf));
+	TEST_RES(read_file(MNT "/gc", buf, sizeof(buf)),
+		 _ret == GC_FILE_SIZE && memcmp(buf, gc_data, GC_FILE_SIZE) == 0);
+	TEST_RES(read_file(MNT "/dir/renamed", buf, sizeof(buf)),
+		 _ret == 5 && memcmp(buf, "hello", 5) == 0);
+
+	TEST_SUCC(unlink(MNT "/gc"));
+	TEST_SUCC(unlink(MNT "/link"));
+	TEST_SUCC(unlink(MNT "/dir/renamed"));
+	TEST_SUCC(rmdir(MNT "/dir"));
+}
+END_TEST()
+
+FN_SETUP(cleanup)
+{
+	CHECK(umount(MNT));
+	CHECK(rmdir(MNT));
+}
+END_SETUP()
@@ -0,0 +1,245 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include "../network/test.h"
+#include <fcntl.h>
+#include <stdint.h>
+#include <string.h>
+#include <sys/ioctl.h>
+#include <unistd.h>
+
+#define FS_VERITY_HASH_ALG_SHA256 1
+
+#define FS_VERITY_METADATA_TYPE_MERKLE_TREE 1
+#define FS_VERITY_METADATA_TYPE_DESCRIPTOR 2
+#define FS_VERITY_METADATA_TYPE_SIGNATURE 3
+
+struct fsverity_enable_arg {
+	uint32_t version;
+	uint32_t hash_algorithm;
+	uint32_t block_size;
+	uint32_t salt_size;
+	uint64_t salt_ptr;
+	uint32_t sig_size;
+	uint32_t __reserved1;
+	uint64_t sig_ptr;
+	uint64_t __reserved2[11];
+};
+
+struct fsverity_digest {
+	uint16_t digest_algorithm;
+	uint16_t digest_size;
+	uint8_t digest[64];
+};
+
+struct fsverity_read_metadata_arg {
+	uint64_t metadata_type;
+	uint64_t offset;
+	uint64_t length;
+	uint64_t buf_ptr;
+	uint64_t __reserved;
+};
+
+#define FS_IOC_ENABLE_VERITY _IOW('f', 133, struct fsverity_enable_arg)
+#define FS_IOC_MEASURE_VERITY _IOWR('f', 134, uint16_t[2])
+#define FS_IOC_READ_VERITY_METADATA \
+	_IOWR('f', 135, struct fsverity_read_metadata_arg)
+
+#define FILE_PLAIN "/ext2/test_fsverity_plain"
+#define FILE_SALTED "/ext2/test_fsverity_salted"
+#define FILE_SIZE (3 * 4096 + 100)
+
+// The file digests and the root hash are computed as Linux does for the test data.
+static const uint8_t plain_digest[32] = {
+	0x8f, 0x9f, 0x99, 0xdc, 0xbe, 0xbc, 0xc8, 0x83, 0x1e, 0xa1, 0x80,
+	0xdc, 0xf9, 0x66, 0x3c, 0xc1, 0x59, 0x10, 0x8f, 0x37, 0x83, 0x8b,
+	0xbb, 0xe6, 0x7a, 0x68, 0xb9, 0x6b, 0x36, 0x1f, 0x0a, 0xb7,
+};
+static const uint8_t plain_root_hash[32] = {
+	0x06, 0x8f, 0x4d, 0xbf, 0x77, 0xa6, 0x54, 0xd5, 0xcc, 0x07, 0x68,
+	0xfa, 0x26, 0x2d, 0x8c, 0xaf, 0x36, 0xf3, 0x2a, 0xb9, 0x6b, 0x22,
+	0xc8, 0xa4, 0x89, 0xae, 0xfe, 0x80, 0x3d, 0x8c, 0x2a, 0x1d,
+};
+static const uint8_t salted_digest[32] = {
+	0x9b, 0xf5, 0x52, 0xe8, 0x34, 0x5d, 0x61, 0x35, 0xd9, 0x3f, 0x5e,
+	0x15, 0x14, 0xcb, 0x3f, 0xee, 0xe3, 0xd2, 0x7f, 0x6f, 0x72, 0x64,
+	0x23, 0x88, 0xf3, 0x4f, 0x40, 0x58, 0xdd, 0x5c, 0xd0, 0x42,
+};
+
+static uint8_t data[FILE_SIZE];
+static uint8_t buf[FILE_SIZE];
+
+static int create_file(const char *path)
+{
+	int fd = open(path, O_RDWR | O_CREAT | O_TRUNC, 0644);
+	if (fd < 0)
+		return -1;
+	if (write(fd, data, FILE_SIZE) != FILE_SIZE) {
+		close(fd);
+		return -1;
+	}
+	return close(fd);
+}
+
+static int enable_verity(int fd, const char *salt)
+{
+	struct fsverity_enable_arg arg;
+
+	memset(&arg, 0, sizeof(arg));
+	arg.version = 1;
+	arg.hash_algorithm = FS_VERITY_HASH_ALG_SHA256;
+	arg.block_size = 4096;
+	if (salt != NULL) {
+		arg.salt_size = strlen(salt);
+		arg.salt_ptr = (uintptr_t)salt;
+	}
+
+	return ioctl(fd, FS_IOC_ENABLE_VERITY, &arg);
+}
+
+static int plain_fd;
+static int salted_fd;
+
+FN_SETUP(files)
+{
+	for (int i = 0; i < FILE_SIZE; i++)
+		data[i] = (uint8_t)(i * 7 + 3);
+
+	CHECK(create_file(FILE_PLAIN));
+	CHECK(create_file(FILE_SALTED));
+	plain_fd = CHECK(open(FILE_PLAIN, O_RDONLY));
+	salted_fd = CHECK(open(FILE_SALTED, O_RDONLY));
+}
+END_SETUP()
+
+FN_TEST(invalid_enable)
+{
+	struct fsverity_enable_arg arg;
+	int fd;
+
+	memset(&arg, 0, sizeof(arg));
+	arg.version = 2;
+	arg.hash_algorithm = FS_VERITY_HASH_ALG_SHA256;
+	arg.block_size = 4096;
+	TEST_ERRNO(ioctl(plain_fd, FS_IOC_ENABLE_VERITY, &arg), EINVAL);
+
+	arg.version = 1;
+	arg.hash_algorithm = 100;
+	TEST_ERRNO(ioctl(plain_fd, FS_IOC_ENABLE_VERITY, &arg), EINVAL);
+
+	arg.hash_algorithm = FS_VERITY_HASH_ALG_SHA256;
+	arg.block_size = 1000;
+	TEST_ERRNO(ioctl(plain_fd, FS_IOC_ENABLE_VERITY, &arg), EINVAL);
+
+	arg.block_size = 4096;
+	arg.salt_size = 33;
+	arg.salt_ptr = (uintptr_t)buf;
+	TEST_ERRNO(ioctl(plain_fd, FS_IOC_ENABLE_VERITY, &arg), EMSGSIZE);
+
+	// Enabling fs-verity fails if the file is opened for writing.
+	fd = TEST_SUCC(open(FILE_PLAIN, O_RDWR));
+	TEST_ERRNO(enable_verity(fd, NULL), ETXTBSY);
+	TEST_SUCC(close(fd));
+
+	fd = TEST_SUCC(open("/ext2", O_RDONLY | O_DIRECTORY));
+	TEST_ERRNO(enable_verity(fd, NULL), EISDIR);
+	TEST_SUCC(close(fd));
+}
+END_TEST()
+
+FN_TEST(measure_without_verity)
+{
+	struct fsverity_digest digest;
+
+	digest.digest_size = sizeof(digest.digest);
+	TEST_ERRNO(ioctl(plain_fd, FS_IOC_MEASURE_VERITY, &digest), ENODATA);
+}
+END_TEST()
+
+FN_TEST(enable)
+{
+	TEST_SUCC(enable_verity(plain_fd, NULL));
+	TEST_ERRNO(enable_verity(plain_fd, NULL), EEXIST);
+	TEST_SUCC(enable_verity(salted_fd, "salt"));
+}
+END_TEST()
+
+FN_TEST(measure)
+{
+	struct fsverity_digest digest;
+
+	digest.digest_size = 31;
+	TEST_ERRNO(ioctl(plain_fd, FS_IOC_MEASURE_VERITY, &digest), EOVERFLOW);
+	TEST_RES(digest.digest_size, _ret == 32);
+
+	digest.digest_size = sizeof(digest.digest);
+	TEST_RES(ioctl(plain_fd, FS_IOC_MEASURE_VERITY, &digest),
+		 digest.digest_algorithm == FS_VERITY_HASH_ALG_SHA256 &&
+			 digest.digest_size == 32 &&
+			 memcmp(digest.digest, plain_digest, 32) == 0);
+
+	digest.digest_size = sizeof(digest.digest);
+	TEST_RES(ioctl(salted_fd, FS_IOC_MEASURE_VERITY, &digest),
+		 digest.digest_algorithm == FS_VERITY_HASH_ALG_SHA256 &&
+			 digest.digest_size == 32 &&
+			 memcmp(digest.digest, salted_digest, 32) == 0);
+}
+END_TEST()
+
+FN_TEST(read_metadata)
+{
+	struct fsverity_read_metadata_arg arg;
+
+	memset(&arg, 0, sizeof(arg));
+	arg.metadata_type = FS_VERITY_METADATA_TYPE_DESCRIPTOR;
+	arg.length = sizeof(buf);
+	arg.buf_ptr = (uintptr_t)buf;
+	TEST_RES(ioctl(plain_fd, FS_IOC_READ_VERITY_METADATA, &arg),
+		 _ret == 256 && buf[0] == 1 &&
+			 buf[1] == FS_VERITY_HASH_ALG_SHA256 && buf[2] == 12 &&
+			 buf[3] == 0 && *(uint64_t *)&buf[8] == FILE_SIZE &&
+			 memcmp(&buf[16], plain_root_hash, 32) == 0);
+
+	// The Merkle tree has one level with four hashes.
+	arg.metadata_type = FS_VERITY_METADATA_TYPE_MERKLE_TREE;
+	TEST_RES(ioctl(plain_fd, FS_IOC_READ_VERITY_METADATA, &arg),
+		 _ret == 4096);
+
+	arg.offset = 4096;
+	TEST_RES(ioctl(plain_fd, FS_IOC_READ_VERITY_METADATA, &arg),
+		 _ret == 0);
+
+	arg.offset = 0;
+	arg.metadata_type = FS_VERITY_METADATA_TYPE_SIGNATURE;
+	TEST_ERRNO(ioctl(plain_fd, FS_IOC_READ_VERITY_METADATA, &arg),
+		   ENODATA);
+}
+END_TEST()
+
+FN_TEST(read_data)
+{
+	TEST_RES(pread(plain_fd, buf, sizeof(buf), 0),
+		 _ret == FILE_SIZE && memcmp(buf, data, FILE_SIZE) == 0);
+	TEST_RES(pread(salted_fd, buf, 200, 3 * 4096 - 100),
+		 _ret == 200 && memcmp(buf, &data[3 * 4096 - 100], 200) == 0);
+}
+END_TEST()
+
+FN_TEST(write_protected)
+{
+	TEST_ERRNO(open(FILE_PLAIN, O_WRONLY), EPERM);
+	TEST_ERRNO(open(FILE_PLAIN, O_RDWR), EPERM);
+	TEST_ERRNO(truncate(FILE_PLAIN, 0), EPERM);
+	TEST_ERRNO(write(plain_fd, data, 1), EBADF);
+}
+END_TEST()
+
+FN_SETUP(cleanup)
+{
+	CHECK(close(plain_fd));
+	CHECK(close(salted_fd));
+	CHECK(unlink(FILE_PLAIN));
+	CHECK(unlink(FILE_SALTED));
+}
+END_SETUP()
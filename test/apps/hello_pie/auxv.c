@@ -0,0 +1,67 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#include <elf.h>
+#include <link.h>
+#include <sys/auxv.h>
+#include <unistd.h>
+
+#include "../network/test.h"
+
+extern char _start[];
+
+FN_TEST(program_headers)
+{
+	const ElfW(Phdr) *phdr = (const ElfW(Phdr) *)getauxval(AT_PHDR);
+	unsigned long phnum = getauxval(AT_PHNUM);
+	const ElfW(Ehdr) *ehdr = NULL;
+	unsigned long i;
+
+	TEST_RES(getauxval(AT_PHENT), _ret == sizeof(ElfW(Phdr)));
+	TEST_RES(phnum, _ret > 0);
+
+	// The PIE is linked at zero, so the ELF header is mapped at the load bias, which can be
+	// derived from where the program headers say they are mapped.
+	for (i = 0; i < phnum; ++i)
+		if (phdr[i].p_type == PT_PHDR)
+			ehdr = (const ElfW(Ehdr) *)((unsigned long)phdr -
+						    phdr[i].p_vaddr);
+	TEST_RES((unsigned long)ehdr, _ret != 0);
+	TEST_RES(ehdr->e_phnum, _ret == phnum);
+	TEST_RES((unsigned long)ehdr + ehdr->e_entry,
+		 _ret == getauxval(AT_ENTRY));
+}
+END_TEST()
+
+FN_TEST(entry_and_interpreter)
+{
+	TEST_RES(getauxval(AT_ENTRY), _ret == (unsigned long)_start);
+	TEST_RES(getauxval(AT_BASE), _ret != 0);
+	TEST_RES(getauxval(AT_RANDOM), _ret != 0);
+	TEST_RES(getauxval(AT_PAGESZ), _ret == (unsigned long)getpagesize());
+	TEST_RES(getauxval(AT_CLKTCK), _ret == 100);
+	TEST_RES(getauxval(AT_FLAGS), _ret == 0);
+}
+END_TEST()
+
+FN_TEST(hwcap)
+{
+#ifdef __riscv
+	// The base integer ISA is always available.
+	TEST_RES(getauxval(AT_HWCAP), _ret & (1UL << ('i' - 'a')));
+	// The vector extension is not exposed to the user space.
+	TEST_RES(getauxval(AT_HWCAP), (_ret & (1UL << ('v' - 'a'))) == 0);
+#else
+	TEST_RES(getauxval(AT_HWCAP), _ret != 0);
+#endif
+}
+END_TEST()
+
+FN_TEST(credentials)
+{
+	TEST_RES(getauxval(AT_UID), _ret == getuid());
+	TEST_RES(getauxval(AT_EUID), _ret == geteuid());
+	TEST_RES(getauxval(AT_GID), _ret == getgid());
+	TEST_RES(getauxval(AT_EGID), _ret == getegid());
+	TEST_RES(getauxval(AT_SECURE), _ret == 0);
+}
+END_TEST()
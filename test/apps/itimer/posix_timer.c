@@ -0,0 +1,190 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include "../network/test.h"
+#include <errno.h>
+#include <signal.h>
+#include <string.h>
+#include <sys/syscall.h>
+#include <time.h>
+#include <unistd.h>
+
+#define SIG SIGRTMIN
+#define SIGVAL_INT 42
+
+static volatile sig_atomic_t nr_signals;
+static volatile int last_code, last_overrun, last_value;
+
+static void handle_signal(int sig, siginfo_t *si, void *unused)
+{
+	++nr_signals;
+	last_code = si->si_code;
+	last_overrun = si->si_overrun;
+	last_value = si->si_value.sival_int;
+}
+
+FN_SETUP(install_handler)
+{
+	struct sigaction sa;
+
+	memset(&sa, 0, sizeof(sa));
+	sa.sa_flags = SA_SIGINFO;
+	sa.sa_sigaction = handle_signal;
+	sigemptyset(&sa.sa_mask);
+	CHECK(sigaction(SIG, &sa, NULL));
+}
+END_SETUP()
+
+static int sleep_ms(long ms)
+{
+	struct timespec ts = { ms / 1000, (ms % 1000) * 1000000 };
+
+	return nanosleep(&ts, NULL);
+}
+
+static int set_signal_blocked(int how)
+{
+	sigset_t set;
+
+	sigemptyset(&set);
+	sigaddset(&set, SIG);
+	return sigprocmask(how, &set, NULL);
+}
+
+static int create_timer(clockid_t clockid, timer_t *timer)
+{
+	struct sigevent sev;
+
+	memset(&sev, 0, sizeof(sev));
+	sev.sigev_notify = SIGEV_SIGNAL;
+	sev.sigev_signo = SIG;
+	sev.sigev_value.sival_int = SIGVAL_INT;
+	return timer_create(clockid, &sev, timer);
+}
+
+FN_TEST(overrun)
+{
+	struct itimerspec its = { { 0, 1000000 }, { 0, 1000000 } };
+	timer_t timer;
+
+	TEST_SUCC(create_timer(CLOCK_MONOTONIC, &timer));
+	TEST_RES(timer_getoverrun(timer), _ret == 0);
+
+	// The expirations are counted as overruns while the signal is blocked.
+	nr_signals = 0;
+	TEST_SUCC(set_signal_blocked(SIG_BLOCK));
+	TEST_SUCC(timer_settime(timer, 0, &its, NULL));
+	TEST_SUCC(sleep_ms(100));
+	TEST_SUCC(set_signal_blocked(SIG_UNBLOCK));
+	TEST_RES(nr_signals, _ret == 1 && last_code == SI_TIMER &&
+				     last_value == SIGVAL_INT && last_overrun >= 10);
+	TEST_RES(timer_getoverrun(timer), _ret == last_overrun);
+
+	TEST_SUCC(timer_delete(timer));
+	TEST_ERRNO(timer_getoverrun(timer), EINVAL);
+}
+END_TEST()
+
+FN_TEST(absolute_time)
+{
+	struct itimerspec its = { { 0, 0 }, { 0, 0 } };
+	timer_t timer;
+
+	TEST_SUCC(create_timer(CLOCK_REALTIME, &timer));
+	TEST_SUCC(clock_gettime(CLOCK_REALTIME, &its.it_value));
+	its.it_value.tv_nsec += 10000000;
+	if (its.it_value.tv_nsec >= 1000000000) {
+		its.it_value.tv_nsec -= 1000000000;
+		++its.it_value.tv_sec;
+	}
+
+	nr_signals = 0;
+	TEST_SUCC(set_signal_blocked(SIG_BLOCK));
+	TEST_SUCC(timer_settime(timer, TIMER_ABSTIME, &its, NULL));
+	TEST_SUCC(sleep_ms(100));
+	TEST_SUCC(set_signal_blocked(SIG_UNBLOCK));
+	TEST_RES(nr_signals, _ret == 1 && last_overrun == 0);
+	TEST_RES(timer_gettime(timer, &its),
+		 its.it_value.tv_sec == 0 && its.it_value.tv_nsec == 0);
+
+	TEST_SUCC(timer_delete(timer));
+}
+END_TEST()
+
+FN_TEST(cpu_clock)
+{
+	struct itimerspec its = { { 0, 0 }, { 0, 50000000 } };
+	timer_t timer;
+
+	TEST_SUCC(create_timer(CLOCK_PROCESS_CPUTIME_ID, &timer));
+
+	nr_signals = 0;
+	TEST_SUCC(timer_settime(timer, 0, &its, NULL));
+	// The CPU time does not elapse during sleeping.
+	TEST_SUCC(sleep_ms(100));
+	TEST_RES(nr_signals, _ret == 0);
+	TEST_RES(timer_gettime(timer, &its),
+		 its.it_value.tv_sec == 0 && its.it_value.tv_nsec > 0);
+
+	while (nr_signals == 0)
+		;
+	TEST_RES(nr_signals, _ret == 1 && last_code == SI_TIMER);
+
+	TEST_SUCC(timer_delete(timer));
+}
+END_TEST()
+
+FN_TEST(thread_id)
+{
+	struct itimerspec its = { { 0, 0 }, { 0, 1000000 } };
+	struct sigevent sev;
+	timer_t timer;
+
+	memset(&sev, 0, sizeof(sev));
+	sev.sigev_notify = SIGEV_THREAD_ID;
+	sev.sigev_signo = SIG;
+	sev.sigev_value.sival_int = SIGVAL_INT + 1;
+	sev._sigev_un._tid = gettid();
+	TEST_SUCC(timer_create(CLOCK_MONOTONIC, &sev, &timer));
+
+	nr_signals = 0;
+	TEST_SUCC(set_signal_blocked(SIG_BLOCK));
+	TEST_SUCC(timer_settime(timer, 0, &its, NULL));
+	TEST_SUCC(sleep_ms(100));
+	TEST_SUCC(set_signal_blocked(SIG_UNBLOCK));
+	TEST_RES(nr_signals, _ret == 1 && last_value == SIGVAL_INT + 1);
+
+	TEST_SUCC(timer_delete(timer));
+
+	sev._sigev_un._tid = 0x7fffffff;
+	TEST_ERRNO(timer_create(CLOCK_MONOTONIC, &sev, &timer), EINVAL);
+}
+END_TEST()
+
+FN_TEST(invalid)
+{
+	struct sigevent sev;
+	int timer_id;
+
+	memset(&sev, 0, sizeof(sev));
+	sev.sigev_notify = SIGEV_SIGNAL;
+	sev.sigev_signo = 0;
+	TEST_ERRNO(syscall(SYS_timer_create, CLOCK_MONOTONIC, &sev, &timer_id),
+		   EINVAL);
+
+	// The kernel treats `SIGEV_THREAD` as `SIGEV_SIGNAL`.
+	sev.sigev_notify = SIGEV_THREAD;
+	TEST_ERRNO(syscall(SYS_timer_create, CLOCK_MONOTONIC, &sev, &timer_id),
+		   EINVAL);
+	sev.sigev_signo = SIG;
+	TEST_SUCC(syscall(SYS_timer_create, CLOCK_MONOTONIC, &sev, &timer_id));
+	TEST_SUCC(syscall(SYS_timer_delete, timer_id));
+
+	sev.sigev_notify = SIGEV_SIGNAL;
+	TEST_ERRNO(syscall(SYS_timer_create, CLOCK_MONOTONIC_RAW, &sev,
+			   &timer_id),
+		   EOPNOTSUPP);
+	TEST_ERRNO(syscall(SYS_timer_getoverrun, 0x7fff), EINVAL);
+}
+END_TEST()
@@ -0,0 +1,216 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include "../network/test.h"
+#include <string.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define KEY_SPEC_THREAD_KEYRING -1
+#define KEY_SPEC_PROCESS_KEYRING -2
+#define KEY_SPEC_SESSION_KEYRING -3
+#define KEY_SPEC_USER_KEYRING -4
+
+#define KEYCTL_GET_KEYRING_ID 0
+#define KEYCTL_UPDATE 2
+#define KEYCTL_REVOKE 3
+#define KEYCTL_DESCRIBE 6
+#define KEYCTL_LINK 8
+#define KEYCTL_UNLINK 9
+#define KEYCTL_SEARCH 10
+#define KEYCTL_READ 11
+#define KEYCTL_INVALIDATE 21
+
+static long add_key(const char *type, const char *description,
+		    const void *payload, size_t len, int keyring)
+{
+	return syscall(SYS_add_key, type, description, payload, len, keyring);
+}
+
+static long request_key(const char *type, const char *description,
+			int dest_keyring)
+{
+	return syscall(SYS_request_key, type, description, NULL, dest_keyring);
+}
+
+static long keyctl(int cmd, unsigned long arg2, unsigned long arg3,
+		   unsigned long arg4, unsigned long arg5)
+{
+	return syscall(SYS_keyctl, cmd, arg2, arg3, arg4, arg5);
+}
+
+static int session;
+static int user_key;
+static char buf[256];
+
+FN_SETUP(session_keyring)
+{
+	session = CHECK_WITH(
+		keyctl(KEYCTL_GET_KEYRING_ID, KEY_SPEC_SESSION_KEYRING, 1, 0, 0),
+		_ret > 0);
+	user_key = CHECK_WITH(add_key("user", "test:user", "secret", 6,
+				      KEY_SPEC_SESSION_KEYRING),
+			      _ret > 0);
+}
+END_SETUP()
+
+FN_TEST(invalid_keys)
+{
+	TEST_ERRNO(add_key("no_such_type", "desc", "x", 1,
+			   KEY_SPEC_SESSION_KEYRING),
+		   ENODEV);
+	TEST_ERRNO(add_key("user", "", "x", 1, KEY_SPEC_SESSION_KEYRING),
+		   EINVAL);
+	TEST_ERRNO(add_key("logon", "no_prefix", "x", 1,
+			   KEY_SPEC_SESSION_KEYRING),
+		   EINVAL);
+	TEST_ERRNO(add_key("keyring", "ring", "x", 1, KEY_SPEC_SESSION_KEYRING),
+		   EINVAL);
+	TEST_ERRNO(add_key("user", "desc", "x", 1, user_key), ENOTDIR);
+	TEST_ERRNO(keyctl(KEYCTL_READ, 0x7fffffff, (unsigned long)buf,
+			  sizeof(buf), 0),
+		   ENOKEY);
+	TEST_ERRNO(keyctl(1000, 0, 0, 0, 0), EOPNOTSUPP);
+}
+END_TEST()
+
+FN_TEST(read_and_update)
+{
+	TEST_RES(keyctl(KEYCTL_READ, user_key, (unsigned long)buf, sizeof(buf),
+			0),
+		 _ret == 6 && memcmp(buf, "secret", 6) == 0);
+	TEST_RES(keyctl(KEYCTL_READ, user_key, (unsigned long)buf, 2, 0),
+		 _ret == 6);
+
+	TEST_SUCC(keyctl(KEYCTL_UPDATE, user_key, (unsigned long)"changed", 7,
+			 0));
+	TEST_RES(keyctl(KEYCTL_READ, user_key, (unsigned long)buf, sizeof(buf),
+			0),
+		 _ret == 7 && memcmp(buf, "changed", 7) == 0);
+
+	// Adding a key of the same type and description updates the key.
+	TEST_RES(add_key("user", "test:user", "secret", 6,
+			 KEY_SPEC_SESSION_KEYRING),
+		 _ret == user_key);
+	TEST_RES(keyctl(KEYCTL_READ, user_key, (unsigned long)buf, sizeof(buf),
+			0),
+		 _ret == 6 && memcmp(buf, "secret", 6) == 0);
+}
+END_TEST()
+
+FN_TEST(describe)
+{
+	TEST_RES(keyctl(KEYCTL_DESCRIBE, user_key, (unsigned long)buf,
+			sizeof(buf), 0),
+		 _ret == (long)strlen(buf) + 1 &&
+			 strncmp(buf, "user;", 5) == 0 &&
+			 strcmp(buf + strlen(buf) - 10, ";test:user") == 0);
+	TEST_RES(keyctl(KEYCTL_DESCRIBE, session, (unsigned long)buf,
+			sizeof(buf), 0),
+		 strncmp(buf, "keyring;", 8) == 0);
+}
+END_TEST()
+
+FN_TEST(search)
+{
+	TEST_RES(request_key("user", "test:user", 0), _ret == user_key);
+	TEST_RES(keyctl(KEYCTL_SEARCH, session, (unsigned long)"user",
+			(unsigned long)"test:user", 0),
+		 _ret == user_key);
+	TEST_ERRNO(request_key("user", "test:none", 0), ENOKEY);
+
+	// Keys in a nested keyring are found as well.
+	int ring = TEST_RES(add_key("keyring", "nested", NULL, 0,
+				    KEY_SPEC_SESSION_KEYRING),
+			    _ret > 0);
+	int nested_key = TEST_RES(add_key("user", "test:nested", "x", 1, ring),
+				  _ret > 0);
+	TEST_RES(request_key("user", "test:nested", 0), _ret == nested_key);
+
+	// Linking makes no cycles.
+	TEST_ERRNO(keyctl(KEYCTL_LINK, session, ring, 0, 0), EDEADLK);
+	TEST_ERRNO(keyctl(KEYCTL_LINK, ring, ring, 0, 0), EDEADLK);
+
+	TEST_SUCC(keyctl(KEYCTL_UNLINK, ring, session, 0, 0));
+	TEST_ERRNO(keyctl(KEYCTL_UNLINK, ring, session, 0, 0), ENOENT);
+	TEST_ERRNO(request_key("user", "test:nested", 0), ENOKEY);
+}
+END_TEST()
+
+FN_TEST(logon_key)
+{
+	int key = TEST_RES(add_key("logon", "test:logon", "secret", 6,
+				   KEY_SPEC_SESSION_KEYRING),
+			   _ret > 0);
+	TEST_ERRNO(keyctl(KEYCTL_READ, key, (unsigned long)buf, sizeof(buf), 0),
+		   EOPNOTSUPP);
+	TEST_RES(request_key("logon", "test:logon", 0), _ret == key);
+}
+END_TEST()
+
+FN_TEST(thread_and_process_keyrings)
+{
+	TEST_ERRNO(keyctl(KEYCTL_GET_KEYRING_ID, KEY_SPEC_THREAD_KEYRING, 0, 0,
+			  0),
+		   ENOKEY);
+	int process_key = TEST_RES(add_key("user", "test:process", "x", 1,
+					   KEY_SPEC_PROCESS_KEYRING),
+				   _ret > 0);
+	TEST_RES(keyctl(KEYCTL_GET_KEYRING_ID, KEY_SPEC_PROCESS_KEYRING, 0, 0,
+			0),
+		 _ret > 0);
+	TEST_RES(request_key("user", "test:process", 0), _ret == process_key);
+	TEST_RES(keyctl(KEYCTL_GET_KEYRING_ID, KEY_SPEC_USER_KEYRING, 0, 0, 0),
+		 _ret > 0);
+}
+END_TEST()
+
+FN_TEST(fork)
+{
+	int process_key = TEST_RES(request_key("user", "test:process", 0),
+				   _ret > 0);
+
+	pid_t pid = TEST_SUCC(fork());
+	if (pid == 0) {
+		// The session keyring is inherited, but the process keyring is not.
+		if (keyctl(KEYCTL_GET_KEYRING_ID, KEY_SPEC_SESSION_KEYRING, 0,
+			   0, 0) != session)
+			_exit(1);
+		if (request_key("user", "test:user", 0) != user_key)
+			_exit(2);
+		if (request_key("user", "test:process", 0) != -1 ||
+		    errno != ENOKEY)
+			_exit(3);
+		if (keyctl(KEYCTL_READ, process_key, (unsigned long)buf,
+			   sizeof(buf), 0) != -1 ||
+		    errno != EACCES)
+			_exit(4);
+		_exit(0);
+	}
+
+	int status;
+	TEST_RES(waitpid(pid, &status, 0),
+		 _ret == pid && WIFEXITED(status) && WEXITSTATUS(status) == 0);
+}
+END_TEST()
+
+FN_TEST(revoke_and_invalidate)
+{
+	int key = TEST_RES(add_key("user", "test:revoke", "x", 1,
+				   KEY_SPEC_SESSION_KEYRING),
+			   _ret > 0);
+	TEST_SUCC(keyctl(KEYCTL_REVOKE, key, 0, 0, 0));
+	TEST_ERRNO(keyctl(KEYCTL_READ, key, (unsigned long)buf, sizeof(buf), 0),
+		   EKEYREVOKED);
+	TEST_ERRNO(request_key("user", "test:revoke", 0), ENOKEY);
+	TEST_SUCC(keyctl(KEYCTL_UNLINK, key, session, 0, 0));
+
+	TEST_SUCC(keyctl(KEYCTL_INVALIDATE, user_key, 0, 0, 0));
+	TEST_ERRNO(keyctl(KEYCTL_READ, user_key, (unsigned long)buf,
+			  sizeof(buf), 0),
+		   ENOKEY);
+	TEST_ERRNO(request_key("user", "test:user", 0), ENOKEY);
+}
+END_TEST()
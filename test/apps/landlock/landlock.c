@@ -0,0 +1,329 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include "../network/test.h"
+#include <fcntl.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/prctl.h>
+#include <sys/stat.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#ifndef SYS_landlock_create_ruleset
+#define SYS_landlock_create_ruleset 444
+#define SYS_landlock_add_rule 445
+#define SYS_landlock_restrict_self 446
+#endif
+
+#define LANDLOCK_CREATE_RULESET_VERSION (1U << 0)
+#define LANDLOCK_RULE_PATH_BENEATH 1
+
+#define LANDLOCK_ACCESS_FS_EXECUTE (1ULL << 0)
+#define LANDLOCK_ACCESS_FS_WRITE_FILE (1ULL << 1)
+#define LANDLOCK_ACCESS_FS_READ_FILE (1ULL << 2)
+#define LANDLOCK_ACCESS_FS_READ_DIR (1ULL << 3)
+#define LANDLOCK_ACCESS_FS_REMOVE_FILE (1ULL << 5)
+#define LANDLOCK_ACCESS_FS_MAKE_REG (1ULL << 8)
+
+struct landlock_ruleset_attr {
+	uint64_t handled_access_fs;
+};
+
+struct landlock_path_beneath_attr {
+	uint64_t allowed_access;
+	int32_t parent_fd;
+} __attribute__((packed));
+
+static int create_ruleset(const void *attr, size_t size, uint32_t flags)
+{
+	return syscall(SYS_landlock_create_ruleset, attr, size, flags);
+}
+
+static int add_rule(int ruleset_fd, uint64_t allowed_access, int parent_fd)
+{
+	struct landlock_path_beneath_attr attr = {
+		.allowed_access = allowed_access,
+		.parent_fd = parent_fd,
+	};
+
+	return syscall(SYS_landlock_add_rule, ruleset_fd,
+		       LANDLOCK_RULE_PATH_BENEATH, &attr, 0);
+}
+
+static int restrict_self(int ruleset_fd)
+{
+	return syscall(SYS_landlock_restrict_self, ruleset_fd, 0);
+}
+
+#define BASE_DIR "/tmp/landlock_test"
+#define ALLOWED_DIR BASE_DIR "/allowed"
+#define DENIED_DIR BASE_DIR "/denied"
+
+#define HANDLED_ACCESS                                                    \
+	(LANDLOCK_ACCESS_FS_WRITE_FILE | LANDLOCK_ACCESS_FS_READ_FILE |   \
+	 LANDLOCK_ACCESS_FS_READ_DIR | LANDLOCK_ACCESS_FS_REMOVE_FILE | \
+	 LANDLOCK_ACCESS_FS_MAKE_REG)
+
+static int create_file(const char *path)
+{
+	int fd = open(path, O_WRONLY | O_CREAT | O_TRUNC, 0644);
+	if (fd < 0)
+		return -1;
+	return close(fd);
+}
+
+FN_SETUP(dirs)
+{
+	CHECK(mkdir(BASE_DIR, 0755));
+	CHECK(mkdir(ALLOWED_DIR, 0755));
+	CHECK(mkdir(DENIED_DIR, 0755));
+	CHECK(create_file(ALLOWED_DIR "/file"));
+	CHECK(create_file(DENIED_DIR "/file"));
+}
+END_SETUP()
+
+FN_TEST(abi_version)
+{
+	TEST_RES(create_ruleset(NULL, 0, LANDLOCK_CREATE_RULESET_VERSION),
+		 _ret == 1);
+	TEST_ERRNO(create_ruleset(NULL, 1, LANDLOCK_CREATE_RULESET_VERSION),
+		   EINVAL);
+}
+END_TEST()
+
+FN_TEST(invalid_ruleset)
+{
+	struct landlock_ruleset_attr attr = { .handled_access_fs = 0 };
+	uint64_t extended_attr[2] = { LANDLOCK_ACCESS_FS_READ_FILE, 0 };
+	int fd;
+
+	TEST_ERRNO(create_ruleset(&attr, sizeof(attr), 0), ENOMSG);
+
+	attr.handled_access_fs = 1ULL << 40;
+	TEST_ERRNO(create_ruleset(&attr, sizeof(attr), 0), EINVAL);
+
+	attr.handled_access_fs = LANDLOCK_ACCESS_FS_READ_FILE;
+	TEST_ERRNO(create_ruleset(&attr, sizeof(attr) - 1, 0), EINVAL);
+	TEST_ERRNO(create_ruleset(&attr, sizeof(attr), 2), EINVAL);
+
+	// The attributes of the later ABI versions must be zeros.
+	fd = TEST_SUCC(
+		create_ruleset(extended_attr, sizeof(extended_attr), 0));
+	TEST_SUCC(close(fd));
+	extended_attr[1] = 1;
+	TEST_ERRNO(create_ruleset(extended_attr, sizeof(extended_attr), 0),
+		   E2BIG);
+}
+END_TEST()
+
+FN_TEST(invalid_rule)
+{
+	struct landlock_ruleset_attr attr = {
+		.handled_access_fs = LANDLOCK_ACCESS_FS_READ_FILE |
+				     LANDLOCK_ACCESS_FS_READ_DIR,
+	};
+	struct landlock_path_beneath_attr path_attr;
+	int ruleset_fd, dir_fd, file_fd;
+
+	ruleset_fd = TEST_SUCC(create_ruleset(&attr, sizeof(attr), 0));
+	dir_fd = TEST_SUCC(open(ALLOWED_DIR, O_PATH | O_DIRECTORY));
+	file_fd = TEST_SUCC(open(ALLOWED_DIR "/file", O_PATH));
+
+	TEST_ERRNO(add_rule(ruleset_fd, 0, dir_fd), ENOMSG);
+	TEST_ERRNO(add_rule(ruleset_fd, LANDLOCK_ACCESS_FS_WRITE_FILE, dir_fd),
+		   EINVAL);
+	TEST_ERRNO(add_rule(ruleset_fd, LANDLOCK_ACCESS_FS_READ_DIR, file_fd),
+		   EINVAL);
+	TEST_ERRNO(add_rule(dir_fd, LANDLOCK_ACCESS_FS_READ_FILE, dir_fd),
+		   EBADFD);
+
+	path_attr.allowed_access = LANDLOCK_ACCESS_FS_READ_FILE;
+	path_attr.parent_fd = dir_fd;
+	TEST_ERRNO(syscall(SYS_landlock_add_rule, ruleset_fd, 2, &path_attr, 0),
+		   EINVAL);
+	TEST_ERRNO(syscall(SYS_landlock_add_rule, ruleset_fd,
+			   LANDLOCK_RULE_PATH_BENEATH, &path_attr, 1),
+		   EINVAL);
+
+	TEST_SUCC(add_rule(ruleset_fd, LANDLOCK_ACCESS_FS_READ_FILE, file_fd));
+	TEST_SUCC(add_rule(ruleset_fd, LANDLOCK_ACCESS_FS_READ_DIR, dir_fd));
+	TEST_ERRNO(syscall(SYS_landlock_restrict_self, ruleset_fd, 1), EINVAL);
+	TEST_ERRNO(restrict_self(dir_fd), EBADFD);
+
+	TEST_SUCC(close(file_fd));
+	TEST_SUCC(close(dir_fd));
+	TEST_SUCC(close(ruleset_fd));
+}
+END_TEST()
+
+FN_TEST(no_new_privs)
+{
+	pid_t pid = TEST_SUCC(fork());
+	if (pid == 0) {
+		if (prctl(PR_GET_NO_NEW_PRIVS, 0, 0, 0, 0) != 0)
+			_exit(1);
+		if (prctl(PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0)
+			_exit(2);
+		if (prctl(PR_GET_NO_NEW_PRIVS, 0, 0, 0, 0) != 1)
+			_exit(3);
+		// The flag cannot be cleared.
+		if (prctl(PR_SET_NO_NEW_PRIVS, 0, 0, 0, 0) != -1 ||
+		    errno != EINVAL)
+			_exit(4);
+		_exit(0);
+	}
+
+	int status;
+	TEST_RES(waitpid(pid, &status, 0),
+		 _ret == pid && WIFEXITED(status) && WEXITSTATUS(status) == 0);
+}
+END_TEST()
+
+static int restrict_to_allowed_dir(void)
+{
+	struct landlock_ruleset_attr attr = {
+		.handled_access_fs = HANDLED_ACCESS,
+	};
+	int ruleset_fd, dir_fd;
+
+	ruleset_fd = create_ruleset(&attr, sizeof(attr), 0);
+	if (ruleset_fd < 0)
+		return -1;
+	dir_fd = open(ALLOWED_DIR, O_PATH | O_DIRECTORY);
+	if (dir_fd < 0)
+		return -1;
+	if (add_rule(ruleset_fd, HANDLED_ACCESS, dir_fd) < 0)
+		return -1;
+	if (prctl(PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) < 0)
+		return -1;
+	if (restrict_self(ruleset_fd) < 0)
+		return -1;
+	close(dir_fd);
+	return close(ruleset_fd);
+}
+
+static int expect_errno(int ret, int err)
+{
+	return ret == -1 && errno == err;
+}
+
+FN_TEST(restricted)
+{
+	pid_t pid = TEST_SUCC(fork());
+	if (pid == 0) {
+		int fd;
+
+		if (restrict_to_allowed_dir() < 0)
+			_exit(1);
+
+		// The access beneath the allowed directory is allowed.
+		fd = open(ALLOWED_DIR "/file", O_RDWR);
+		if (fd < 0)
+			_exit(2);
+		close(fd);
+		fd = open(ALLOWED_DIR, O_RDONLY | O_DIRECTORY);
+		if (fd < 0)
+			_exit(3);
+		close(fd);
+		if (create_file(ALLOWED_DIR "/new") < 0)
+			_exit(4);
+		if (rename(ALLOWED_DIR "/new", ALLOWED_DIR "/renamed") < 0)
+			_exit(5);
+		if (unlink(ALLOWED_DIR "/renamed") < 0)
+			_exit(6);
+
+		// The handled access elsewhere is denied.
+		if (!expect_errno(open(DENIED_DIR "/file", O_RDONLY), EACCES))
+			_exit(7);
+		if (!expect_errno(open(DENIED_DIR, O_RDONLY | O_DIRECTORY),
+				  EACCES))
+			_exit(8);
+		if (!expect_errno(create_file(DENIED_DIR "/new"), EACCES))
+			_exit(9);
+		if (!expect_errno(unlink(DENIED_DIR "/file"), EACCES))
+			_exit(10);
+		if (!expect_errno(rename(ALLOWED_DIR "/file",
+					 DENIED_DIR "/moved"),
+				  EXDEV))
+			_exit(11);
+
+		// The access that is not handled is allowed.
+		fd = open(DENIED_DIR "/file", O_PATH);
+		if (fd < 0)
+			_exit(12);
+		close(fd);
+		if (mkdir(DENIED_DIR "/dir", 0755) < 0)
+			_exit(13);
+		if (rmdir(DENIED_DIR "/dir") < 0)
+			_exit(14);
+
+		_exit(0);
+	}
+
+	int status;
+	TEST_RES(waitpid(pid, &status, 0),
+		 _ret == pid && WIFEXITED(status) && WEXITSTATUS(status) == 0);
+
+	// The parent is not restricted.
+	TEST_SUCC(create_file(DENIED_DIR "/new"));
+	TEST_SUCC(unlink(DENIED_DIR "/new"));
+}
+END_TEST()
+
+FN_TEST(inherited_and_stacked)
+{
+	pid_t pid = TEST_SUCC(fork());
+	if (pid == 0) {
+		struct landlock_ruleset_attr attr = {
+			.handled_access_fs = LANDLOCK_ACCESS_FS_WRITE_FILE,
+		};
+		int ruleset_fd, fd;
+
+		if (restrict_to_allowed_dir() < 0)
+			_exit(1);
+
+		// A new ruleset that allows nothing to be written is stacked.
+		ruleset_fd = create_ruleset(&attr, sizeof(attr), 0);
+		if (ruleset_fd < 0 || restrict_self(ruleset_fd) < 0)
+			_exit(2);
+
+		pid_t child = fork();
+		if (child == 0) {
+			if (!expect_errno(open(DENIED_DIR "/file", O_RDONLY),
+					  EACCES))
+				_exit(1);
+			if (!expect_errno(open(ALLOWED_DIR "/file", O_WRONLY),
+					  EACCES))
+				_exit(2);
+			fd = open(ALLOWED_DIR "/file", O_RDONLY);
+			if (fd < 0)
+				_exit(3);
+			_exit(0);
+		}
+
+		int status;
+		if (waitpid(child, &status, 0) != child ||
+		    !WIFEXITED(status) || WEXITSTATUS(status) != 0)
+			_exit(3);
+		_exit(0);
+	}
+
+	int status;
+	TEST_RES(waitpid(pid, &status, 0),
+		 _ret == pid && WIFEXITED(status) && WEXITSTATUS(status) == 0);
+}
+END_TEST()
+
+FN_SETUP(cleanup)
+{
+	CHECK(unlink(ALLOWED_DIR "/file"));
+	CHECK(unlink(DENIED_DIR "/file"));
+	CHECK(rmdir(ALLOWED_DIR));
+	CHECK(rmdir(DENIED_DIR));
+	CHECK(rmdir(BASE_DIR));
+}
+END_SETUP()
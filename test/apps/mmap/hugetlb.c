@@ -0,0 +1,200 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include "../network/test.h"
+#include <fcntl.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/mman.h>
+#include <sys/mount.h>
+#include <sys/stat.h>
+#include <unistd.h>
+
+#define HUGE_PAGE_SIZE (2UL << 20)
+#define NR_HUGE_PAGES 4
+#define MOUNT_POINT "/tmp/hugetlbfs"
+
+static int set_nr_hugepages(int nr_pages)
+{
+	char buf[16];
+	int fd, len;
+
+	fd = open("/proc/sys/vm/nr_hugepages", O_WRONLY);
+	if (fd < 0)
+		return -1;
+	len = snprintf(buf, sizeof(buf), "%d\n", nr_pages);
+	if (write(fd, buf, len) != len) {
+		close(fd);
+		return -1;
+	}
+	return close(fd);
+}
+
+static long get_nr_hugepages(void)
+{
+	char buf[16];
+	ssize_t len;
+	int fd;
+
+	fd = open("/proc/sys/vm/nr_hugepages", O_RDONLY);
+	if (fd < 0)
+		return -1;
+	len = read(fd, buf, sizeof(buf) - 1);
+	close(fd);
+	if (len <= 0)
+		return -1;
+	buf[len] = '\0';
+	return atol(buf);
+}
+
+static long get_meminfo(const char *field)
+{
+	char line[128];
+	size_t field_len = strlen(field);
+	long value = -1;
+	FILE *file;
+
+	file = fopen("/proc/meminfo", "r");
+	if (file == NULL)
+		return -1;
+	while (fgets(line, sizeof(line), file) != NULL) {
+		if (strncmp(line, field, field_len) == 0 &&
+		    line[field_len] == ':') {
+			value = atol(line + field_len + 1);
+			break;
+		}
+	}
+	fclose(file);
+	return value;
+}
+
+FN_SETUP(reserve_pool)
+{
+	CHECK(set_nr_hugepages(NR_HUGE_PAGES));
+	CHECK_WITH(get_nr_hugepages(), _ret == NR_HUGE_PAGES);
+}
+END_SETUP()
+
+FN_TEST(meminfo)
+{
+	TEST_RES(get_meminfo("Hugepagesize"), _ret == HUGE_PAGE_SIZE / 1024);
+	TEST_RES(get_meminfo("HugePages_Total"), _ret == NR_HUGE_PAGES);
+	TEST_RES(get_meminfo("HugePages_Free"), _ret == NR_HUGE_PAGES);
+	TEST_RES(get_meminfo("HugePages_Rsvd"), _ret == 0);
+}
+END_TEST()
+
+FN_TEST(anonymous_mapping)
+{
+	char *addr;
+
+	addr = (char *)TEST_SUCC(
+		(long)mmap(NULL, HUGE_PAGE_SIZE, PROT_READ | PROT_WRITE,
+			   MAP_PRIVATE | MAP_ANONYMOUS | MAP_HUGETLB, -1, 0));
+	TEST_RES((unsigned long)addr & (HUGE_PAGE_SIZE - 1), _ret == 0);
+
+	// The page is reserved when mapped and allocated when touched.
+	TEST_RES(get_meminfo("HugePages_Rsvd"), _ret == 1);
+	TEST_RES(get_meminfo("HugePages_Free"), _ret == NR_HUGE_PAGES);
+	addr[0] = 1;
+	addr[HUGE_PAGE_SIZE - 1] = 2;
+	TEST_RES(get_meminfo("HugePages_Rsvd"), _ret == 0);
+	TEST_RES(get_meminfo("HugePages_Free"), _ret == NR_HUGE_PAGES - 1);
+	TEST_RES(addr[0] + addr[HUGE_PAGE_SIZE - 1], _ret == 3);
+
+	TEST_SUCC(munmap(addr, HUGE_PAGE_SIZE));
+	TEST_RES(get_meminfo("HugePages_Free"), _ret == NR_HUGE_PAGES);
+}
+END_TEST()
+
+FN_TEST(pool_exhausted)
+{
+	char *addr;
+
+	// The length is rounded up to the huge pages.
+	addr = (char *)TEST_SUCC(
+		(long)mmap(NULL, NR_HUGE_PAGES * HUGE_PAGE_SIZE - 1,
+			   PROT_READ | PROT_WRITE,
+			   MAP_SHARED | MAP_ANONYMOUS | MAP_HUGETLB, -1, 0));
+	TEST_RES(get_meminfo("HugePages_Rsvd"), _ret == NR_HUGE_PAGES);
+
+	TEST_ERRNO((long)mmap(NULL, HUGE_PAGE_SIZE, PROT_READ | PROT_WRITE,
+			      MAP_PRIVATE | MAP_ANONYMOUS | MAP_HUGETLB, -1,
+			      0),
+		   ENOMEM);
+
+	TEST_SUCC(munmap(addr, NR_HUGE_PAGES * HUGE_PAGE_SIZE));
+	TEST_RES(get_meminfo("HugePages_Rsvd"), _ret == 0);
+}
+END_TEST()
+
+FN_TEST(invalid_mappings)
+{
+	// The size of the huge pages is not supported.
+	TEST_ERRNO((long)mmap(NULL, HUGE_PAGE_SIZE, PROT_READ | PROT_WRITE,
+			      MAP_PRIVATE | MAP_ANONYMOUS | MAP_HUGETLB |
+				      (13 << MAP_HUGE_SHIFT),
+			      -1, 0),
+		   EINVAL);
+
+	// The fixed address is not aligned to the huge pages.
+	TEST_ERRNO((long)mmap((void *)(0x40000000UL + 4096), HUGE_PAGE_SIZE,
+			      PROT_READ | PROT_WRITE,
+			      MAP_PRIVATE | MAP_ANONYMOUS | MAP_HUGETLB |
+				      MAP_FIXED,
+			      -1, 0),
+		   EINVAL);
+}
+END_TEST()
+
+FN_TEST(hugetlbfs)
+{
+	struct stat stat;
+	char *addr;
+	int fd;
+
+	TEST_SUCC(mkdir(MOUNT_POINT, 0755));
+	TEST_SUCC(mount("none", MOUNT_POINT, "hugetlbfs", 0, "pagesize=2M"));
+
+	fd = TEST_SUCC(open(MOUNT_POINT "/file", O_CREAT | O_RDWR, 0644));
+
+	// The file cannot be written, but can be mapped.
+	TEST_ERRNO(write(fd, "a", 1), EINVAL);
+	addr = (char *)TEST_SUCC((long)mmap(NULL, HUGE_PAGE_SIZE,
+					    PROT_READ | PROT_WRITE, MAP_SHARED,
+					    fd, 0));
+	TEST_RES(fstat(fd, &stat), stat.st_size == HUGE_PAGE_SIZE &&
+					   stat.st_blksize == HUGE_PAGE_SIZE);
+	TEST_RES(get_meminfo("HugePages_Rsvd"), _ret == 1);
+	strcpy(addr, "hello");
+	TEST_SUCC(munmap(addr, HUGE_PAGE_SIZE));
+
+	// The offset must be aligned to the huge pages.
+	TEST_ERRNO((long)mmap(NULL, HUGE_PAGE_SIZE, PROT_READ, MAP_SHARED, fd,
+			      4096),
+		   EINVAL);
+
+	// The page stays in the file after it is unmapped.
+	addr = (char *)TEST_SUCC(
+		(long)mmap(NULL, HUGE_PAGE_SIZE, PROT_READ, MAP_SHARED, fd, 0));
+	TEST_RES(strcmp(addr, "hello"), _ret == 0);
+	TEST_SUCC(munmap(addr, HUGE_PAGE_SIZE));
+	TEST_RES(get_meminfo("HugePages_Free"), _ret == NR_HUGE_PAGES - 1);
+
+	// The page is freed after the file is removed.
+	TEST_SUCC(close(fd));
+	TEST_SUCC(unlink(MOUNT_POINT "/file"));
+	TEST_RES(get_meminfo("HugePages_Free"), _ret == NR_HUGE_PAGES);
+
+	TEST_SUCC(umount(MOUNT_POINT));
+	TEST_SUCC(rmdir(MOUNT_POINT));
+}
+END_TEST()
+
+FN_SETUP(release_pool)
+{
+	CHECK(set_nr_hugepages(0));
+}
+END_SETUP()
@@ -0,0 +1,175 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#include <fcntl.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/mman.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#include "../network/test.h"
+
+#define PAGE_SIZE 4096
+#define NR_PAGES 16
+
+#ifndef KSM_DIR
+#define KSM_DIR "/proc/sys/vm/ksm"
+#endif
+
+static long read_ksm(const char *name)
+{
+	char path[64], buf[32];
+	ssize_t len;
+	int fd;
+
+	snprintf(path, sizeof(path), "%s/%s", KSM_DIR, name);
+	fd = open(path, O_RDONLY);
+	if (fd < 0)
+		return -1;
+	len = read(fd, buf, sizeof(buf) - 1);
+	close(fd);
+	if (len <= 0)
+		return -1;
+	buf[len] = '\0';
+	return atol(buf);
+}
+
+static int write_ksm(const char *name, const char *value)
+{
+	char path[64];
+	ssize_t len;
+	int fd;
+
+	snprintf(path, sizeof(path), "%s/%s", KSM_DIR, name);
+	fd = open(path, O_WRONLY);
+	if (fd < 0)
+		return -1;
+	len = write(fd, value, strlen(value));
+	close(fd);
+	return len == strlen(value) ? 0 : -1;
+}
+
+static char *map_anonymous(int flags)
+{
+	char *addr;
+
+	addr = mmap(NULL, PAGE_SIZE * NR_PAGES, PROT_READ | PROT_WRITE,
+		    flags | MAP_ANONYMOUS, -1, 0);
+	CHECK(addr == MAP_FAILED ? -1 : 0);
+	return addr;
+}
+
+static void fill_pages(char *addr)
+{
+	for (int i = 0; i < NR_PAGES; ++i)
+		memset(addr + i * PAGE_SIZE, 'a' + i % 2, PAGE_SIZE);
+}
+
+static int check_pages(char *addr)
+{
+	for (int i = 0; i < NR_PAGES; ++i)
+		for (int j = 0; j < PAGE_SIZE; ++j)
+			if (addr[i * PAGE_SIZE + j] != 'a' + i % 2)
+				return -1;
+	return 0;
+}
+
+// Waits until all the mergeable pages have been scanned twice, after which the pages that
+// are not changed should have been merged.
+static int wait_full_scans(void)
+{
+	long full_scans = read_ksm("full_scans");
+
+	for (int i = 0; i < 500; ++i) {
+		if (read_ksm("full_scans") >= full_scans + 2)
+			return 0;
+		usleep(10000);
+	}
+	return -1;
+}
+
+FN_TEST(invalid_ranges)
+{
+	char *addr;
+
+	addr = map_anonymous(MAP_PRIVATE);
+
+	TEST_ERRNO(madvise(addr + 1, PAGE_SIZE, MADV_MERGEABLE), EINVAL);
+
+	TEST_SUCC(munmap(addr + PAGE_SIZE, PAGE_SIZE));
+	TEST_ERRNO(madvise(addr, PAGE_SIZE * 2, MADV_MERGEABLE), ENOMEM);
+	TEST_ERRNO(madvise(addr, PAGE_SIZE * 2, MADV_UNMERGEABLE), ENOMEM);
+
+	TEST_SUCC(munmap(addr, PAGE_SIZE * NR_PAGES));
+}
+END_TEST()
+
+FN_TEST(shared_mappings)
+{
+	char *addr;
+
+	// The shared mappings are not merged, but advising them succeeds like Linux.
+	addr = map_anonymous(MAP_SHARED);
+	TEST_SUCC(madvise(addr, PAGE_SIZE * NR_PAGES, MADV_MERGEABLE));
+	TEST_SUCC(madvise(addr, PAGE_SIZE * NR_PAGES, MADV_UNMERGEABLE));
+	TEST_SUCC(munmap(addr, PAGE_SIZE * NR_PAGES));
+}
+END_TEST()
+
+FN_TEST(merge_and_break_out)
+{
+	char *addr, *other;
+	long pages_sharing;
+	int status;
+	pid_t pid;
+
+	addr = map_anonymous(MAP_PRIVATE);
+	other = map_anonymous(MAP_PRIVATE);
+	fill_pages(addr);
+	fill_pages(other);
+
+	TEST_SUCC(madvise(addr, PAGE_SIZE * NR_PAGES, MADV_MERGEABLE));
+	TEST_SUCC(madvise(other, PAGE_SIZE * NR_PAGES, MADV_MERGEABLE));
+
+	TEST_SUCC(write_ksm("sleep_millisecs", "0"));
+	TEST_SUCC(write_ksm("run", "1"));
+	TEST_SUCC(wait_full_scans());
+	TEST_RES(read_ksm("pages_shared"), _ret >= 2);
+	pages_sharing = TEST_RES(read_ksm("pages_sharing"),
+				 _ret >= NR_PAGES * 2 - 2);
+
+	// The merged pages are broken out by the writes.
+	TEST_RES(check_pages(addr), _ret == 0);
+	addr[PAGE_SIZE] = 'c';
+	TEST_RES(check_pages(other), _ret == 0);
+	addr[PAGE_SIZE] = 'b';
+	TEST_RES(check_pages(addr), _ret == 0);
+
+	// The writes of the child are not visible to the parent.
+	pid = TEST_SUCC(fork());
+	if (pid == 0) {
+		memset(other, 'c', PAGE_SIZE * NR_PAGES);
+		_exit(0);
+	}
+	TEST_RES(wait(&status), _ret == pid && WIFEXITED(status) &&
+					WEXITSTATUS(status) == 0);
+	TEST_RES(check_pages(addr), _ret == 0);
+	TEST_RES(check_pages(other), _ret == 0);
+
+	// The pages are no longer merged after being unmerged.
+	TEST_SUCC(madvise(addr, PAGE_SIZE * NR_PAGES, MADV_UNMERGEABLE));
+	TEST_SUCC(madvise(other, PAGE_SIZE * NR_PAGES, MADV_UNMERGEABLE));
+	TEST_SUCC(wait_full_scans());
+	TEST_RES(read_ksm("pages_sharing"),
+		 _ret <= pages_sharing - (NR_PAGES * 2 - 2));
+	TEST_RES(check_pages(addr), _ret == 0);
+	TEST_RES(check_pages(other), _ret == 0);
+
+	TEST_SUCC(write_ksm("run", "0"));
+	TEST_SUCC(write_ksm("sleep_millisecs", "20"));
+
+	TEST_SUCC(munmap(addr, PAGE_SIZE * NR_PAGES));
+	TEST_SUCC(munmap(other, PAGE_SIZE * NR_PAGES));
+}
+END_TEST()
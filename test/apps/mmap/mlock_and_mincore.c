@@ -0,0 +1,115 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <stdio.h>
+#include <sys/mman.h>
+#include <unistd.h>
+
+#include "../network/test.h"
+
+#define PAGE_SIZE 4096
+#define NR_PAGES 4
+
+static char *addr;
+
+static int residency(char *start, size_t nr_pages)
+{
+	unsigned char vec[NR_PAGES];
+	int i, result = 0;
+
+	if (mincore(start, nr_pages * PAGE_SIZE, vec) < 0)
+		return -1;
+	for (i = 0; i < nr_pages; ++i)
+		result |= (vec[i] & 1) << i;
+	return result;
+}
+
+static long locked_kb(void)
+{
+	char line[128];
+	long value = -1;
+	FILE *file;
+
+	file = fopen("/proc/self/status", "r");
+	if (file == NULL)
+		return -1;
+	while (fgets(line, sizeof(line), file) != NULL)
+		if (sscanf(line, "VmLck: %ld kB", &value) == 1)
+			break;
+	fclose(file);
+	return value;
+}
+
+FN_SETUP(mmap)
+{
+	addr = mmap(NULL, PAGE_SIZE * (NR_PAGES + 1), PROT_READ | PROT_WRITE,
+		    MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+	CHECK(addr == MAP_FAILED ? -1 : 0);
+	// Leave a hole after the pages.
+	CHECK(munmap(addr + PAGE_SIZE * NR_PAGES, PAGE_SIZE));
+}
+END_SETUP()
+
+FN_TEST(mincore)
+{
+	TEST_RES(residency(addr, NR_PAGES), _ret == 0);
+
+	addr[PAGE_SIZE] = 1;
+	TEST_RES(residency(addr, NR_PAGES), _ret == 0x2);
+
+	TEST_ERRNO(mincore(addr + 1, PAGE_SIZE, (unsigned char[1]){}), EINVAL);
+	TEST_ERRNO(mincore(addr, PAGE_SIZE * (NR_PAGES + 1),
+			   (unsigned char[NR_PAGES + 1]){}),
+		   ENOMEM);
+	TEST_ERRNO(mincore(addr, PAGE_SIZE, NULL), EFAULT);
+}
+END_TEST()
+
+FN_TEST(mlock)
+{
+	// The range is extended to the page boundaries.
+	TEST_SUCC(mlock(addr + PAGE_SIZE * 2 + 1, PAGE_SIZE));
+	TEST_RES(residency(addr, NR_PAGES), _ret == 0xe);
+	TEST_RES(locked_kb(), _ret == PAGE_SIZE * 2 / 1024);
+
+	TEST_SUCC(munlock(addr, PAGE_SIZE * NR_PAGES));
+	TEST_RES(locked_kb(), _ret == 0);
+
+	TEST_ERRNO(mlock(addr, PAGE_SIZE * (NR_PAGES + 1)), ENOMEM);
+	TEST_ERRNO(mlock2(addr, PAGE_SIZE, 2), EINVAL);
+}
+END_TEST()
+
+FN_TEST(mlock_onfault)
+{
+	TEST_SUCC(mlock2(addr, PAGE_SIZE * NR_PAGES, MLOCK_ONFAULT));
+	TEST_RES(residency(addr, NR_PAGES), _ret == 0xe);
+	TEST_RES(locked_kb(), _ret == PAGE_SIZE * NR_PAGES / 1024);
+	TEST_SUCC(munlockall());
+	TEST_RES(locked_kb(), _ret == 0);
+}
+END_TEST()
+
+FN_TEST(mlockall)
+{
+	char *new_addr;
+
+	TEST_ERRNO(mlockall(0), EINVAL);
+	TEST_ERRNO(mlockall(MCL_ONFAULT), EINVAL);
+
+	TEST_SUCC(mlockall(MCL_FUTURE));
+	new_addr = mmap(NULL, PAGE_SIZE * NR_PAGES, PROT_READ | PROT_WRITE,
+			MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+	TEST_RES(residency(new_addr, NR_PAGES), _ret == 0xf);
+	TEST_SUCC(munlockall());
+
+	TEST_SUCC(munmap(new_addr, PAGE_SIZE * NR_PAGES));
+}
+END_TEST()
+
+FN_SETUP(munmap)
+{
+	CHECK(munmap(addr, PAGE_SIZE * NR_PAGES));
+}
+END_SETUP()
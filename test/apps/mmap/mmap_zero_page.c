@@ -0,0 +1,94 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#include <sys/mman.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#include "../network/test.h"
+
+#define PAGE_SIZE 4096
+#define NR_PAGES 16
+
+static char *map_anonymous(int prot)
+{
+	char *addr;
+
+	addr = mmap(NULL, PAGE_SIZE * NR_PAGES, prot,
+		    MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+	CHECK(addr == MAP_FAILED ? -1 : 0);
+	return addr;
+}
+
+static int sum_pages(char *addr)
+{
+	int i, sum = 0;
+
+	for (i = 0; i < NR_PAGES; ++i)
+		sum += ((volatile char *)addr)[i * PAGE_SIZE + i];
+	return sum;
+}
+
+FN_TEST(read_then_write)
+{
+	char *addr, *other;
+
+	addr = map_anonymous(PROT_READ | PROT_WRITE);
+	other = map_anonymous(PROT_READ | PROT_WRITE);
+
+	// Reading the untouched pages yields zeros.
+	TEST_RES(sum_pages(addr), _ret == 0);
+	TEST_RES(sum_pages(other), _ret == 0);
+
+	// Writing a page that has been read does not affect the other pages.
+	addr[PAGE_SIZE + 1] = 1;
+	TEST_RES(sum_pages(addr), _ret == 1);
+	TEST_RES(sum_pages(other), _ret == 0);
+
+	TEST_SUCC(munmap(addr, PAGE_SIZE * NR_PAGES));
+	TEST_SUCC(munmap(other, PAGE_SIZE * NR_PAGES));
+}
+END_TEST()
+
+FN_TEST(mprotect_after_read)
+{
+	char *addr, *other;
+
+	addr = map_anonymous(PROT_READ);
+	other = map_anonymous(PROT_READ);
+	TEST_RES(sum_pages(addr), _ret == 0);
+	TEST_RES(sum_pages(other), _ret == 0);
+
+	// Making the pages writable does not make them share the writes.
+	TEST_SUCC(mprotect(addr, PAGE_SIZE * NR_PAGES, PROT_READ | PROT_WRITE));
+	addr[PAGE_SIZE * 2 + 2] = 2;
+	TEST_RES(sum_pages(addr), _ret == 2);
+	TEST_RES(sum_pages(other), _ret == 0);
+
+	TEST_SUCC(munmap(addr, PAGE_SIZE * NR_PAGES));
+	TEST_SUCC(munmap(other, PAGE_SIZE * NR_PAGES));
+}
+END_TEST()
+
+FN_TEST(fork_after_read)
+{
+	char *addr;
+	int status;
+	pid_t pid;
+
+	addr = map_anonymous(PROT_READ | PROT_WRITE);
+	TEST_RES(sum_pages(addr), _ret == 0);
+
+	pid = TEST_SUCC(fork());
+	if (pid == 0) {
+		addr[PAGE_SIZE * 3 + 3] = 3;
+		_exit(sum_pages(addr));
+	}
+	TEST_RES(wait(&status), _ret == pid && WIFEXITED(status) &&
+					WEXITSTATUS(status) == 3);
+
+	// The writes of the child are not visible to the parent.
+	TEST_RES(sum_pages(addr), _ret == 0);
+
+	TEST_SUCC(munmap(addr, PAGE_SIZE * NR_PAGES));
+}
+END_TEST()
@@ -0,0 +1,206 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include "../network/test.h"
+#include <errno.h>
+#include <fcntl.h>
+#include <mqueue.h>
+#include <poll.h>
+#include <signal.h>
+#include <string.h>
+#include <time.h>
+#include <unistd.h>
+
+#define QUEUE_NAME "/asterinas_test_mqueue"
+#define MAXMSG 4
+#define MSGSIZE 16
+
+static mqd_t mqd;
+
+FN_SETUP(create)
+{
+	struct mq_attr attr = { .mq_maxmsg = MAXMSG, .mq_msgsize = MSGSIZE };
+
+	mq_unlink(QUEUE_NAME);
+	mqd = CHECK(mq_open(QUEUE_NAME, O_RDWR | O_CREAT | O_EXCL | O_NONBLOCK,
+			    0600, &attr));
+}
+END_SETUP()
+
+FN_TEST(open_errors)
+{
+	struct mq_attr attr = { .mq_maxmsg = 0, .mq_msgsize = MSGSIZE };
+
+	TEST_ERRNO(mq_open(QUEUE_NAME, O_RDWR | O_CREAT | O_EXCL, 0600, NULL),
+		   EEXIST);
+	TEST_ERRNO(mq_open("/asterinas_test_none", O_RDWR), ENOENT);
+	TEST_ERRNO(mq_open("/asterinas/test", O_RDWR | O_CREAT, 0600, NULL),
+		   EACCES);
+	TEST_ERRNO(mq_open("/asterinas_test_none", O_RDWR | O_CREAT, 0600,
+			   &attr),
+		   EINVAL);
+}
+END_TEST()
+
+FN_TEST(priority_order)
+{
+	char buf[MSGSIZE];
+	unsigned int prio;
+
+	TEST_SUCC(mq_send(mqd, "low", 4, 1));
+	TEST_SUCC(mq_send(mqd, "high1", 6, 5));
+	TEST_SUCC(mq_send(mqd, "high2", 6, 5));
+	TEST_SUCC(mq_send(mqd, "mid", 4, 3));
+	TEST_ERRNO(mq_send(mqd, "full", 5, 0), EAGAIN);
+
+	TEST_RES(mq_receive(mqd, buf, sizeof(buf), &prio),
+		 _ret == 6 && prio == 5 && strcmp(buf, "high1") == 0);
+	TEST_RES(mq_receive(mqd, buf, sizeof(buf), &prio),
+		 _ret == 6 && prio == 5 && strcmp(buf, "high2") == 0);
+	TEST_RES(mq_receive(mqd, buf, sizeof(buf), &prio),
+		 _ret == 4 && prio == 3 && strcmp(buf, "mid") == 0);
+	TEST_RES(mq_receive(mqd, buf, sizeof(buf), NULL),
+		 _ret == 4 && strcmp(buf, "low") == 0);
+	TEST_ERRNO(mq_receive(mqd, buf, sizeof(buf), NULL), EAGAIN);
+}
+END_TEST()
+
+FN_TEST(message_errors)
+{
+	char buf[MSGSIZE + 1] = { 0 };
+
+	TEST_ERRNO(mq_send(mqd, buf, MSGSIZE + 1, 0), EMSGSIZE);
+	TEST_ERRNO(mq_send(mqd, buf, 1, sysconf(_SC_MQ_PRIO_MAX)), EINVAL);
+	TEST_ERRNO(mq_receive(mqd, buf, MSGSIZE - 1, NULL), EMSGSIZE);
+	TEST_ERRNO(mq_send(-1, buf, 1, 0), EBADF);
+	TEST_ERRNO(mq_send(STDIN_FILENO, buf, 1, 0), EBADF);
+}
+END_TEST()
+
+FN_TEST(attributes)
+{
+	struct mq_attr attr;
+	struct mq_attr new_attr = { .mq_flags = 0 };
+
+	TEST_SUCC(mq_send(mqd, "one", 4, 0));
+	TEST_RES(mq_getattr(mqd, &attr),
+		 attr.mq_flags == O_NONBLOCK && attr.mq_maxmsg == MAXMSG &&
+			 attr.mq_msgsize == MSGSIZE && attr.mq_curmsgs == 1);
+
+	TEST_SUCC(mq_setattr(mqd, &new_attr, &attr));
+	TEST_RES(mq_getattr(mqd, &attr), attr.mq_flags == 0);
+
+	new_attr.mq_flags = O_NONBLOCK;
+	TEST_SUCC(mq_setattr(mqd, &new_attr, NULL));
+	TEST_RES(mq_getattr(mqd, &attr), attr.mq_flags == O_NONBLOCK);
+
+	TEST_RES(mq_receive(mqd, (char[MSGSIZE]){}, MSGSIZE, NULL), _ret == 4);
+}
+END_TEST()
+
+FN_TEST(timed_receive)
+{
+	char buf[MSGSIZE];
+	struct mq_attr attr = { .mq_flags = 0 };
+	struct timespec ts;
+
+	TEST_SUCC(mq_setattr(mqd, &attr, NULL));
+
+	TEST_SUCC(clock_gettime(CLOCK_REALTIME, &ts));
+	ts.tv_nsec += 50 * 1000 * 1000;
+	if (ts.tv_nsec >= 1000 * 1000 * 1000) {
+		ts.tv_sec += 1;
+		ts.tv_nsec -= 1000 * 1000 * 1000;
+	}
+	TEST_ERRNO(mq_timedreceive(mqd, buf, sizeof(buf), NULL, &ts),
+		   ETIMEDOUT);
+
+	ts.tv_nsec = 1000 * 1000 * 1000;
+	TEST_ERRNO(mq_timedreceive(mqd, buf, sizeof(buf), NULL, &ts), EINVAL);
+	TEST_ERRNO(mq_timedsend(mqd, "one", 4, 0, &ts), EINVAL);
+
+	attr.mq_flags = O_NONBLOCK;
+	TEST_SUCC(mq_setattr(mqd, &attr, NULL));
+}
+END_TEST()
+
+FN_TEST(notify_signal)
+{
+	struct sigevent sev = { .sigev_notify = SIGEV_SIGNAL,
+				.sigev_signo = SIGUSR1,
+				.sigev_value.sival_int = 42 };
+	sigset_t set;
+	siginfo_t info;
+	struct timespec timeout = { .tv_sec = 1 };
+
+	sigemptyset(&set);
+	sigaddset(&set, SIGUSR1);
+	TEST_SUCC(sigprocmask(SIG_BLOCK, &set, NULL));
+
+	TEST_SUCC(mq_notify(mqd, &sev));
+	TEST_ERRNO(mq_notify(mqd, &sev), EBUSY);
+
+	TEST_SUCC(mq_send(mqd, "one", 4, 0));
+	TEST_RES(sigtimedwait(&set, &info, &timeout),
+		 _ret == SIGUSR1 && info.si_code == SI_MESGQ &&
+			 info.si_pid == getpid() &&
+			 info.si_value.sival_int == 42);
+
+	// The registration is removed after the notification.
+	TEST_SUCC(mq_notify(mqd, &sev));
+	TEST_SUCC(mq_notify(mqd, NULL));
+	TEST_SUCC(mq_notify(mqd, &sev));
+	TEST_SUCC(mq_notify(mqd, NULL));
+
+	// No notification is sent if the queue is not empty.
+	TEST_SUCC(mq_notify(mqd, &sev));
+	TEST_SUCC(mq_send(mqd, "two", 4, 0));
+	timeout.tv_sec = 0;
+	TEST_ERRNO(sigtimedwait(&set, &info, &timeout), EAGAIN);
+	TEST_SUCC(mq_notify(mqd, NULL));
+
+	TEST_RES(mq_receive(mqd, (char[MSGSIZE]){}, MSGSIZE, NULL), _ret == 4);
+	TEST_RES(mq_receive(mqd, (char[MSGSIZE]){}, MSGSIZE, NULL), _ret == 4);
+	TEST_SUCC(sigprocmask(SIG_UNBLOCK, &set, NULL));
+}
+END_TEST()
+
+FN_TEST(poll)
+{
+	struct pollfd pfd = { .fd = mqd, .events = POLLIN | POLLOUT };
+	int i;
+
+	TEST_RES(poll(&pfd, 1, 0), _ret == 1 && pfd.revents == POLLOUT);
+
+	TEST_SUCC(mq_send(mqd, "one", 4, 0));
+	TEST_RES(poll(&pfd, 1, 0),
+		 _ret == 1 && pfd.revents == (POLLIN | POLLOUT));
+
+	for (i = 1; i < MAXMSG; ++i)
+		TEST_SUCC(mq_send(mqd, "more", 5, 0));
+	TEST_RES(poll(&pfd, 1, 0), _ret == 1 && pfd.revents == POLLIN);
+
+	for (i = 0; i < MAXMSG; ++i)
+		TEST_RES(mq_receive(mqd, (char[MSGSIZE]){}, MSGSIZE, NULL),
+			 _ret > 0);
+}
+END_TEST()
+
+FN_TEST(unlink)
+{
+	TEST_SUCC(mq_unlink(QUEUE_NAME));
+	TEST_ERRNO(mq_unlink(QUEUE_NAME), ENOENT);
+	TEST_ERRNO(mq_open(QUEUE_NAME, O_RDWR), ENOENT);
+
+	// The unlinked queue can still be used.
+	TEST_SUCC(mq_send(mqd, "one", 4, 0));
+	TEST_RES(mq_receive(mqd, (char[MSGSIZE]){}, MSGSIZE, NULL), _ret == 4);
+}
+END_TEST()
+
+FN_SETUP(cleanup)
+{
+	CHECK(mq_close(mqd));
+}
+END_SETUP()
@@ -0,0 +1,215 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#include <unistd.h>
+#include <sys/signal.h>
+#include <sys/socket.h>
+#include <netinet/in.h>
+#include <arpa/inet.h>
+
+#include "test.h"
+
+static struct sockaddr_in6 lo_addr;
+static struct sockaddr_in6 mapped_addr;
+static struct sockaddr_in lo4_addr;
+
+#define TCP_PORT htons(0x1357)
+#define UDP_PORT htons(0x2468)
+#define MAPPED_PORT htons(0x1358)
+
+FN_SETUP(general)
+{
+	lo_addr.sin6_family = AF_INET6;
+	lo_addr.sin6_addr = in6addr_loopback;
+
+	mapped_addr.sin6_family = AF_INET6;
+	CHECK_WITH(inet_pton(AF_INET6, "::ffff:127.0.0.1",
+			     &mapped_addr.sin6_addr),
+		   _ret == 1);
+
+	lo4_addr.sin_family = AF_INET;
+	CHECK_WITH(inet_pton(AF_INET, "127.0.0.1", &lo4_addr.sin_addr),
+		   _ret == 1);
+
+	signal(SIGPIPE, SIG_IGN);
+}
+END_SETUP()
+
+FN_TEST(tcp_loopback)
+{
+	struct sockaddr_in6 addr;
+	socklen_t addrlen;
+	int sk_listen, sk_client, sk_accepted;
+	char buf[6];
+
+	sk_listen = TEST_SUCC(socket(AF_INET6, SOCK_STREAM, 0));
+	lo_addr.sin6_port = TCP_PORT;
+	TEST_SUCC(bind(sk_listen, (struct sockaddr *)&lo_addr,
+		       sizeof(lo_addr)));
+	TEST_SUCC(listen(sk_listen, 1));
+
+	sk_client = TEST_SUCC(socket(AF_INET6, SOCK_STREAM, 0));
+	TEST_SUCC(connect(sk_client, (struct sockaddr *)&lo_addr,
+			  sizeof(lo_addr)));
+
+	addrlen = sizeof(addr);
+	sk_accepted = TEST_RES(
+		accept(sk_listen, (struct sockaddr *)&addr, &addrlen),
+		addrlen == sizeof(addr) && addr.sin6_family == AF_INET6 &&
+			IN6_IS_ADDR_LOOPBACK(&addr.sin6_addr));
+
+	addrlen = sizeof(addr);
+	TEST_RES(getsockname(sk_client, (struct sockaddr *)&addr, &addrlen),
+		 addrlen == sizeof(addr) && addr.sin6_family == AF_INET6 &&
+			 IN6_IS_ADDR_LOOPBACK(&addr.sin6_addr) &&
+			 addr.sin6_port != 0);
+
+	addrlen = sizeof(addr);
+	TEST_RES(getpeername(sk_client, (struct sockaddr *)&addr, &addrlen),
+		 addrlen == sizeof(addr) && addr.sin6_family == AF_INET6 &&
+			 IN6_IS_ADDR_LOOPBACK(&addr.sin6_addr) &&
+			 addr.sin6_port == TCP_PORT);
+
+	TEST_RES(send(sk_client, "hello", 6, 0), _ret == 6);
+	TEST_RES(recv(sk_accepted, buf, sizeof(buf), 0),
+		 _ret == 6 && strcmp(buf, "hello") == 0);
+
+	TEST_SUCC(close(sk_accepted));
+	TEST_SUCC(close(sk_client));
+	TEST_SUCC(close(sk_listen));
+}
+END_TEST()
+
+FN_TEST(udp_loopback)
+{
+	struct sockaddr_in6 addr;
+	socklen_t addrlen;
+	int sk_server, sk_client;
+	char buf[6];
+
+	sk_server = TEST_SUCC(socket(AF_INET6, SOCK_DGRAM, 0));
+	lo_addr.sin6_port = UDP_PORT;
+	TEST_SUCC(bind(sk_server, (struct sockaddr *)&lo_addr,
+		       sizeof(lo_addr)));
+
+	sk_client = TEST_SUCC(socket(AF_INET6, SOCK_DGRAM, 0));
+	TEST_RES(sendto(sk_client, "hello", 6, 0, (struct sockaddr *)&lo_addr,
+			sizeof(lo_addr)),
+		 _ret == 6);
+
+	addrlen = sizeof(addr);
+	TEST_RES(recvfrom(sk_server, buf, sizeof(buf), 0,
+			  (struct sockaddr *)&addr, &addrlen),
+		 _ret == 6 && strcmp(buf, "hello") == 0 &&
+			 addrlen == sizeof(addr) &&
+			 addr.sin6_family == AF_INET6 &&
+			 IN6_IS_ADDR_LOOPBACK(&addr.sin6_addr) &&
+			 addr.sin6_port != 0);
+
+	TEST_SUCC(close(sk_client));
+	TEST_SUCC(close(sk_server));
+}
+END_TEST()
+
+FN_TEST(dual_stack)
+{
+	struct sockaddr_in6 addr;
+	socklen_t addrlen;
+	int sk_listen, sk_client, sk_accepted;
+
+	sk_listen = TEST_SUCC(socket(AF_INET6, SOCK_STREAM, 0));
+	mapped_addr.sin6_port = MAPPED_PORT;
+	TEST_SUCC(bind(sk_listen, (struct sockaddr *)&mapped_addr,
+		       sizeof(mapped_addr)));
+	TEST_SUCC(listen(sk_listen, 1));
+
+	addrlen = sizeof(addr);
+	TEST_RES(getsockname(sk_listen, (struct sockaddr *)&addr, &addrlen),
+		 addrlen == sizeof(addr) && addr.sin6_family == AF_INET6 &&
+			 IN6_IS_ADDR_V4MAPPED(&addr.sin6_addr) &&
+			 addr.sin6_port == MAPPED_PORT);
+
+	// An IPv4 socket can connect to the IPv6 socket.
+	sk_client = TEST_SUCC(socket(AF_INET, SOCK_STREAM, 0));
+	lo4_addr.sin_port = MAPPED_PORT;
+	TEST_SUCC(connect(sk_client, (struct sockaddr *)&lo4_addr,
+			  sizeof(lo4_addr)));
+
+	addrlen = sizeof(addr);
+	sk_accepted = TEST_RES(
+		accept(sk_listen, (struct sockaddr *)&addr, &addrlen),
+		addrlen == sizeof(addr) && addr.sin6_family == AF_INET6 &&
+			IN6_IS_ADDR_V4MAPPED(&addr.sin6_addr));
+
+	TEST_SUCC(close(sk_accepted));
+	TEST_SUCC(close(sk_client));
+	TEST_SUCC(close(sk_listen));
+}
+END_TEST()
+
+FN_TEST(v6only)
+{
+	int sk, sk4;
+	int val;
+	socklen_t len;
+
+	sk = TEST_SUCC(socket(AF_INET6, SOCK_DGRAM, 0));
+
+	len = sizeof(val);
+	TEST_RES(getsockopt(sk, IPPROTO_IPV6, IPV6_V6ONLY, &val, &len),
+		 len == sizeof(val) && val == 0);
+
+	val = 1;
+	TEST_SUCC(setsockopt(sk, IPPROTO_IPV6, IPV6_V6ONLY, &val, sizeof(val)));
+	len = sizeof(val);
+	TEST_RES(getsockopt(sk, IPPROTO_IPV6, IPV6_V6ONLY, &val, &len),
+		 len == sizeof(val) && val == 1);
+
+	// IPv4-mapped addresses cannot be bound to IPv6-only sockets.
+	mapped_addr.sin6_port = UDP_PORT;
+	TEST_ERRNO(bind(sk, (struct sockaddr *)&mapped_addr,
+			sizeof(mapped_addr)),
+		   EINVAL);
+
+	// The option cannot be changed after binding.
+	lo_addr.sin6_port = 0;
+	TEST_SUCC(bind(sk, (struct sockaddr *)&lo_addr, sizeof(lo_addr)));
+	val = 0;
+	TEST_ERRNO(setsockopt(sk, IPPROTO_IPV6, IPV6_V6ONLY, &val,
+			      sizeof(val)),
+		   EINVAL);
+
+	// IPv4 peers are unreachable from IPv6-only sockets.
+	TEST_ERRNO(sendto(sk, "hello", 6, 0, (struct sockaddr *)&mapped_addr,
+			  sizeof(mapped_addr)),
+		   ENETUNREACH);
+
+	// IPv6-level options are not available for IPv4 sockets.
+	sk4 = TEST_SUCC(socket(AF_INET, SOCK_DGRAM, 0));
+	len = sizeof(val);
+	TEST_ERRNO(getsockopt(sk4, IPPROTO_IPV6, IPV6_V6ONLY, &val, &len),
+		   EOPNOTSUPP);
+	TEST_ERRNO(setsockopt(sk4, IPPROTO_IPV6, IPV6_V6ONLY, &val,
+			      sizeof(val)),
+		   ENOPROTOOPT);
+
+	TEST_SUCC(close(sk4));
+	TEST_SUCC(close(sk));
+}
+END_TEST()
+
+FN_TEST(family_mismatch)
+{
+	struct sockaddr_in6 addr = { .sin6_family = AF_INET };
+	int sk;
+
+	sk = TEST_SUCC(socket(AF_INET6, SOCK_STREAM, 0));
+	TEST_ERRNO(bind(sk, (struct sockaddr *)&addr, sizeof(addr)),
+		   EAFNOSUPPORT);
+	TEST_SUCC(close(sk));
+
+	sk = TEST_SUCC(socket(AF_INET, SOCK_STREAM, 0));
+	TEST_ERRNO(bind(sk, (struct sockaddr *)&lo_addr, sizeof(lo_addr)),
+		   EAFNOSUPPORT);
+	TEST_SUCC(close(sk));
+}
+END_TEST()
@@ -0,0 +1,438 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <arpa/inet.h>
+#include <linux/netfilter.h>
+#include <linux/netlink.h>
+#include <netinet/in.h>
+#include <poll.h>
+#include <sys/socket.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#include "test.h"
+
+/*
+ * The simplified `NETLINK_NETFILTER` protocol. See
+ * `kernel/src/net/socket/netlink/netfilter/message.rs` for details.
+ */
+#define NFL_MSG_NEWCHAIN 16
+#define NFL_MSG_GETCHAIN 18
+#define NFL_MSG_NEWRULE 20
+#define NFL_MSG_DELRULE 21
+#define NFL_MSG_GETRULE 22
+
+#define NFLA_CHAIN_POLICY 1
+
+#define NFLA_RULE_HANDLE 1
+#define NFLA_RULE_PROTOCOL 2
+#define NFLA_RULE_SRC 3
+#define NFLA_RULE_SRC_LEN 4
+#define NFLA_RULE_DST 5
+#define NFLA_RULE_DST_LEN 6
+#define NFLA_RULE_SRC_PORTS 7
+#define NFLA_RULE_DST_PORTS 8
+#define NFLA_RULE_VERDICT 9
+
+struct nfl_msg {
+	unsigned char family;
+	unsigned char hook;
+	unsigned short pad;
+};
+
+struct nfl_port_range {
+	unsigned short min;
+	unsigned short max;
+};
+
+#define BUF_SIZE 8192
+#define TEST_PORT 0x2468
+
+static int sk_nf;
+static int sk_udp;
+static struct sockaddr_in udp_addr;
+static unsigned int seq;
+static char buf[BUF_SIZE];
+
+FN_SETUP(socket)
+{
+	sk_nf = CHECK(socket(AF_NETLINK, SOCK_RAW, NETLINK_NETFILTER));
+
+	udp_addr.sin_family = AF_INET;
+	udp_addr.sin_port = htons(TEST_PORT);
+	CHECK_WITH(inet_pton(AF_INET, "127.0.0.1", &udp_addr.sin_addr),
+		   _ret == 1);
+
+	sk_udp = CHECK(socket(AF_INET, SOCK_DGRAM, 0));
+	CHECK(bind(sk_udp, (struct sockaddr *)&udp_addr, sizeof(udp_addr)));
+}
+END_SETUP()
+
+/*
+ * Sends a UDP packet to `sk_udp` itself. Returns one if the packet arrives, or
+ * zero if it is dropped.
+ */
+static int udp_passes(void)
+{
+	struct pollfd pfd = { .fd = sk_udp, .events = POLLIN };
+	char data = 'x';
+
+	if (sendto(sk_udp, &data, 1, 0, (struct sockaddr *)&udp_addr,
+		   sizeof(udp_addr)) != 1)
+		return -1;
+
+	if (poll(&pfd, 1, 100) != 1)
+		return 0;
+
+	return recv(sk_udp, &data, 1, 0) == 1;
+}
+
+static struct nlmsghdr *init_request(int type, int flags, int family, int hook)
+{
+	struct nlmsghdr *nlh = (struct nlmsghdr *)buf;
+	struct nfl_msg msg = { .family = family, .hook = hook };
+
+	memset(buf, 0, sizeof(buf));
+	nlh->nlmsg_len = NLMSG_LENGTH(sizeof(msg));
+	nlh->nlmsg_type = type;
+	nlh->nlmsg_flags = NLM_F_REQUEST | flags;
+	nlh->nlmsg_seq = ++seq;
+	memcpy(NLMSG_DATA(nlh), &msg, sizeof(msg));
+
+	return nlh;
+}
+
+static void add_attr(struct nlmsghdr *nlh, int type, const void *data,
+		     size_t len)
+{
+	struct nlattr *nla =
+		(struct nlattr *)((char *)nlh + NLMSG_ALIGN(nlh->nlmsg_len));
+
+	nla->nla_type = type;
+	nla->nla_len = NLA_HDRLEN + len;
+	memcpy((char *)nla + NLA_HDRLEN, data, len);
+	nlh->nlmsg_len = NLMSG_ALIGN(nlh->nlmsg_len) + NLA_ALIGN(nla->nla_len);
+}
+
+static void add_attr_u8(struct nlmsghdr *nlh, int type, unsigned char value)
+{
+	add_attr(nlh, type, &value, sizeof(value));
+}
+
+static void add_attr_u32(struct nlmsghdr *nlh, int type, unsigned int value)
+{
+	add_attr(nlh, type, &value, sizeof(value));
+}
+
+static void add_attr_ports(struct nlmsghdr *nlh, int type, unsigned short min,
+			   unsigned short max)
+{
+	struct nfl_port_range ports = { .min = min, .max = max };
+
+	add_attr(nlh, type, &ports, sizeof(ports));
+}
+
+static void add_attr_in_addr(struct nlmsghdr *nlh, int type, const char *addr)
+{
+	struct in_addr in;
+
+	inet_pton(AF_INET, addr, &in);
+	add_attr(nlh, type, &in, sizeof(in));
+}
+
+static struct nlattr *find_attr(struct nlmsghdr *seg, int type)
+{
+	char *pos = (char *)NLMSG_DATA(seg) + NLMSG_ALIGN(sizeof(struct nfl_msg));
+	char *end = (char *)seg + seg->nlmsg_len;
+	struct nlattr *nla;
+
+	while (pos + NLA_HDRLEN <= end) {
+		nla = (struct nlattr *)pos;
+		if (nla->nla_len < NLA_HDRLEN || pos + nla->nla_len > end)
+			break;
+		if (nla->nla_type == type)
+			return nla;
+		pos += NLA_ALIGN(nla->nla_len);
+	}
+
+	return NULL;
+}
+
+static unsigned int attr_u32(struct nlattr *nla)
+{
+	unsigned int value;
+
+	memcpy(&value, (char *)nla + NLA_HDRLEN, sizeof(value));
+	return value;
+}
+
+static int recv_ack(void)
+{
+	struct nlmsghdr *nlh;
+	struct nlmsgerr *err;
+	ssize_t len;
+
+	len = recv(sk_nf, buf, sizeof(buf), 0);
+	if (len < 0)
+		return -1;
+
+	nlh = (struct nlmsghdr *)buf;
+	if (!NLMSG_OK(nlh, len) || nlh->nlmsg_type != NLMSG_ERROR ||
+	    nlh->nlmsg_seq != seq) {
+		errno = EPROTO;
+		return -1;
+	}
+
+	err = NLMSG_DATA(nlh);
+	if (err->error != 0) {
+		errno = -err->error;
+		return -1;
+	}
+
+	return 0;
+}
+
+/*
+ * Sends the request and waits for the acknowledgment. Returns zero on success
+ * and sets errno to the error code on failure.
+ */
+static int send_request(struct nlmsghdr *nlh)
+{
+	nlh->nlmsg_flags |= NLM_F_ACK;
+	if (send(sk_nf, nlh, nlh->nlmsg_len, 0) < 0)
+		return -1;
+
+	return recv_ack();
+}
+
+/*
+ * Sends the request to add a rule, which is echoed back. Returns the handle of
+ * the new rule, or -1 on failure.
+ */
+static int send_rule_request(struct nlmsghdr *nlh)
+{
+	struct nlattr *handle;
+	ssize_t len;
+
+	nlh->nlmsg_flags |= NLM_F_ECHO | NLM_F_ACK;
+	if (send(sk_nf, nlh, nlh->nlmsg_len, 0) < 0)
+		return -1;
+
+	len = recv(sk_nf, buf, sizeof(buf), 0);
+	if (len < 0)
+		return -1;
+
+	nlh = (struct nlmsghdr *)buf;
+	if (!NLMSG_OK(nlh, len) || nlh->nlmsg_seq != seq) {
+		errno = EPROTO;
+		return -1;
+	}
+	if (nlh->nlmsg_type == NLMSG_ERROR) {
+		errno = -((struct nlmsgerr *)NLMSG_DATA(nlh))->error;
+		return -1;
+	}
+
+	handle = find_attr(nlh, NFLA_RULE_HANDLE);
+	if (nlh->nlmsg_type != NFL_MSG_NEWRULE || handle == NULL) {
+		errno = EPROTO;
+		return -1;
+	}
+
+	return recv_ack() < 0 ? -1 : (int)attr_u32(handle);
+}
+
+/*
+ * Sends the dump request and counts the segments for which `match` returns
+ * true. Returns the number of matched segments, or -1 on failure.
+ */
+static int dump(struct nlmsghdr *nlh, int (*match)(struct nlmsghdr *))
+{
+	struct nlmsghdr *seg;
+	ssize_t len;
+	int count = 0;
+
+	nlh->nlmsg_flags |= NLM_F_DUMP;
+	if (send(sk_nf, nlh, nlh->nlmsg_len, 0) < 0)
+		return -1;
+
+	for (;;) {
+		len = recv(sk_nf, buf, sizeof(buf), 0);
+		if (len < 0)
+			return -1;
+
+		for (seg = (struct nlmsghdr *)buf; NLMSG_OK(seg, len);
+		     seg = NLMSG_NEXT(seg, len)) {
+			if (seg->nlmsg_seq != seq) {
+				errno = EPROTO;
+				return -1;
+			}
+			if (seg->nlmsg_type == NLMSG_DONE)
+				return count;
+			if (seg->nlmsg_type == NLMSG_ERROR) {
+				errno = -((struct nlmsgerr *)NLMSG_DATA(seg))
+						 ->error;
+				return -1;
+			}
+			count += match(seg);
+		}
+	}
+}
+
+static int match_rule(struct nlmsghdr *seg)
+{
+	return seg->nlmsg_type == NFL_MSG_NEWRULE;
+}
+
+static struct nlmsghdr *init_drop_rule(int hook, int flags)
+{
+	struct nlmsghdr *nlh;
+
+	nlh = init_request(NFL_MSG_NEWRULE, flags, AF_INET, hook);
+	add_attr_u8(nlh, NFLA_RULE_PROTOCOL, IPPROTO_UDP);
+	add_attr_ports(nlh, NFLA_RULE_DST_PORTS, TEST_PORT, TEST_PORT);
+	add_attr_u32(nlh, NFLA_RULE_VERDICT, NF_DROP);
+
+	return nlh;
+}
+
+FN_TEST(input_rules)
+{
+	struct nlmsghdr *nlh;
+	int drop_handle, accept_handle;
+
+	TEST_RES(udp_passes(), _ret == 1);
+
+	nlh = init_drop_rule(NF_INET_LOCAL_IN, NLM_F_APPEND);
+	drop_handle = TEST_RES(send_rule_request(nlh), _ret > 0);
+	TEST_RES(udp_passes(), _ret == 0);
+
+	nlh = init_request(NFL_MSG_GETRULE, 0, AF_UNSPEC, 0);
+	TEST_RES(dump(nlh, match_rule), _ret == 1);
+
+	// The rule is inserted at the beginning of the chain, so it takes
+	// precedence over the existing rule.
+	nlh = init_request(NFL_MSG_NEWRULE, 0, AF_INET, NF_INET_LOCAL_IN);
+	add_attr_in_addr(nlh, NFLA_RULE_SRC, "127.0.0.0");
+	add_attr_u8(nlh, NFLA_RULE_SRC_LEN, 8);
+	add_attr_u32(nlh, NFLA_RULE_VERDICT, NF_ACCEPT);
+	accept_handle = TEST_RES(send_rule_request(nlh),
+				 _ret > 0 && _ret != drop_handle);
+	TEST_RES(udp_passes(), _ret == 1);
+
+	nlh = init_request(NFL_MSG_DELRULE, 0, AF_INET, NF_INET_LOCAL_IN);
+	add_attr_u32(nlh, NFLA_RULE_HANDLE, accept_handle);
+	TEST_SUCC(send_request(nlh));
+	TEST_RES(udp_passes(), _ret == 0);
+
+	// The handle no longer exists.
+	TEST_ERRNO(send_request(nlh), ENOENT);
+
+	nlh = init_request(NFL_MSG_DELRULE, 0, AF_INET, NF_INET_LOCAL_IN);
+	add_attr_u32(nlh, NFLA_RULE_HANDLE, drop_handle);
+	TEST_SUCC(send_request(nlh));
+	TEST_RES(udp_passes(), _ret == 1);
+}
+END_TEST()
+
+FN_TEST(output_rules)
+{
+	struct nlmsghdr *nlh;
+
+	nlh = init_drop_rule(NF_INET_LOCAL_OUT, NLM_F_APPEND);
+	TEST_RES(send_rule_request(nlh), _ret > 0);
+	nlh = init_drop_rule(NF_INET_LOCAL_OUT, NLM_F_APPEND);
+	TEST_RES(send_rule_request(nlh), _ret > 0);
+	TEST_RES(udp_passes(), _ret == 0);
+
+	// Deleting a rule without its handle flushes the chain.
+	nlh = init_request(NFL_MSG_DELRULE, 0, AF_INET, NF_INET_LOCAL_OUT);
+	TEST_SUCC(send_request(nlh));
+	TEST_RES(udp_passes(), _ret == 1);
+
+	nlh = init_request(NFL_MSG_GETRULE, 0, AF_UNSPEC, 0);
+	TEST_RES(dump(nlh, match_rule), _ret == 0);
+}
+END_TEST()
+
+static int match_drop_chain(struct nlmsghdr *seg)
+{
+	struct nfl_msg *msg = NLMSG_DATA(seg);
+	struct nlattr *policy = find_attr(seg, NFLA_CHAIN_POLICY);
+
+	return seg->nlmsg_type == NFL_MSG_NEWCHAIN &&
+	       msg->hook == NF_INET_PRE_ROUTING && policy != NULL &&
+	       attr_u32(policy) == NF_DROP;
+}
+
+FN_TEST(chain_policy)
+{
+	struct nlmsghdr *nlh;
+
+	nlh = init_request(NFL_MSG_NEWCHAIN, 0, AF_UNSPEC, NF_INET_PRE_ROUTING);
+	add_attr_u32(nlh, NFLA_CHAIN_POLICY, NF_DROP);
+	TEST_SUCC(send_request(nlh));
+	TEST_RES(udp_passes(), _ret == 0);
+
+	nlh = init_request(NFL_MSG_GETCHAIN, 0, AF_UNSPEC, 0);
+	TEST_RES(dump(nlh, match_drop_chain), _ret == 1);
+
+	nlh = init_request(NFL_MSG_NEWCHAIN, 0, AF_UNSPEC, NF_INET_PRE_ROUTING);
+	add_attr_u32(nlh, NFLA_CHAIN_POLICY, NF_ACCEPT);
+	TEST_SUCC(send_request(nlh));
+	TEST_RES(udp_passes(), _ret == 1);
+
+	nlh = init_request(NFL_MSG_GETCHAIN, 0, AF_UNSPEC, 0);
+	TEST_RES(dump(nlh, match_drop_chain), _ret == 0);
+}
+END_TEST()
+
+FN_TEST(invalid_request)
+{
+	struct nlmsghdr *nlh;
+
+	// Packets are never forwarded.
+	nlh = init_drop_rule(NF_INET_FORWARD, 0);
+	TEST_ERRNO(send_request(nlh), EOPNOTSUPP);
+	nlh = init_drop_rule(NF_INET_NUMHOOKS, 0);
+	TEST_ERRNO(send_request(nlh), EINVAL);
+
+	// The verdict is missing.
+	nlh = init_request(NFL_MSG_NEWRULE, 0, AF_INET, NF_INET_LOCAL_IN);
+	TEST_ERRNO(send_request(nlh), EINVAL);
+
+	// The address does not match the address family.
+	nlh = init_request(NFL_MSG_NEWRULE, 0, AF_INET6, NF_INET_LOCAL_IN);
+	add_attr_in_addr(nlh, NFLA_RULE_DST, "127.0.0.1");
+	add_attr_u32(nlh, NFLA_RULE_VERDICT, NF_DROP);
+	TEST_ERRNO(send_request(nlh), EINVAL);
+
+	nlh = init_request(NFL_MSG_NEWCHAIN, 0, AF_UNSPEC, NF_INET_LOCAL_IN);
+	add_attr_u32(nlh, NFLA_CHAIN_POLICY, NF_QUEUE);
+	TEST_ERRNO(send_request(nlh), EINVAL);
+
+	nlh = init_request(0xffff, 0, AF_UNSPEC, 0);
+	TEST_ERRNO(send_request(nlh), EOPNOTSUPP);
+}
+END_TEST()
+
+FN_TEST(permission)
+{
+	struct nlmsghdr *nlh;
+	int status;
+	pid_t pid;
+
+	pid = TEST_SUCC(fork());
+	if (pid == 0) {
+		// Dropping the root privilege also drops `CAP_NET_ADMIN`.
+		CHECK(setuid(65534));
+		nlh = init_request(NFL_MSG_GETCHAIN, 0, AF_UNSPEC,
+				   NF_INET_LOCAL_IN);
+		CHECK_WITH(send_request(nlh), _ret < 0 && errno == EPERM);
+		exit(0);
+	}
+
+	TEST_RES(waitpid(pid, &status, 0),
+		 _ret == pid && WIFEXITED(status) && WEXITSTATUS(status) == 0);
+}
+END_TEST()
@@ -0,0 +1,315 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <arpa/inet.h>
+#include <linux/netlink.h>
+#include <linux/rtnetlink.h>
+#include <net/if.h>
+#include <sys/socket.h>
+#include <unistd.h>
+
+#include "test.h"
+
+#define BUF_SIZE 8192
+
+static int sk_route;
+static int lo_index;
+static unsigned int seq;
+static char buf[BUF_SIZE];
+
+FN_SETUP(socket)
+{
+	sk_route = CHECK(socket(AF_NETLINK, SOCK_RAW, NETLINK_ROUTE));
+	lo_index = CHECK_WITH(if_nametoindex("lo"), _ret > 0);
+}
+END_SETUP()
+
+FN_TEST(invalid_socket)
+{
+	TEST_ERRNO(socket(AF_NETLINK, SOCK_STREAM, NETLINK_ROUTE),
+		   ESOCKTNOSUPPORT);
+	TEST_ERRNO(socket(AF_NETLINK, SOCK_RAW, MAX_LINKS), EPROTONOSUPPORT);
+}
+END_TEST()
+
+FN_TEST(bind_and_getsockname)
+{
+	struct sockaddr_nl addr = { .nl_family = AF_NETLINK };
+	socklen_t addrlen = sizeof(addr);
+	int sk;
+
+	TEST_SUCC(bind(sk_route, (struct sockaddr *)&addr, sizeof(addr)));
+	TEST_RES(getsockname(sk_route, (struct sockaddr *)&addr, &addrlen),
+		 addrlen == sizeof(addr) && addr.nl_family == AF_NETLINK &&
+			 addr.nl_pid != 0 && addr.nl_groups == 0);
+
+	// The port is already in use.
+	sk = TEST_SUCC(socket(AF_NETLINK, SOCK_RAW, NETLINK_ROUTE));
+	TEST_ERRNO(bind(sk, (struct sockaddr *)&addr, sizeof(addr)),
+		   EADDRINUSE);
+	TEST_SUCC(close(sk));
+}
+END_TEST()
+
+static struct nlmsghdr *init_request(int type, int flags, const void *body,
+				     size_t len)
+{
+	struct nlmsghdr *nlh = (struct nlmsghdr *)buf;
+
+	memset(buf, 0, sizeof(buf));
+	nlh->nlmsg_len = NLMSG_LENGTH(len);
+	nlh->nlmsg_type = type;
+	nlh->nlmsg_flags = NLM_F_REQUEST | flags;
+	nlh->nlmsg_seq = ++seq;
+	memcpy(NLMSG_DATA(nlh), body, len);
+
+	return nlh;
+}
+
+static void add_attr(struct nlmsghdr *nlh, int type, const void *data,
+		     size_t len)
+{
+	struct rtattr *rta =
+		(struct rtattr *)((char *)nlh + NLMSG_ALIGN(nlh->nlmsg_len));
+
+	rta->rta_type = type;
+	rta->rta_len = RTA_LENGTH(len);
+	memcpy(RTA_DATA(rta), data, len);
+	nlh->nlmsg_len = NLMSG_ALIGN(nlh->nlmsg_len) + RTA_ALIGN(rta->rta_len);
+}
+
+static void add_attr_in_addr(struct nlmsghdr *nlh, int type, const char *addr)
+{
+	struct in_addr in;
+
+	inet_pton(AF_INET, addr, &in);
+	add_attr(nlh, type, &in, sizeof(in));
+}
+
+/*
+ * Sends the request and waits for the acknowledgment. Returns zero on success
+ * and sets errno to the error code on failure.
+ */
+static int send_request(struct nlmsghdr *nlh)
+{
+	struct nlmsgerr *err;
+	ssize_t len;
+
+	nlh->nlmsg_flags |= NLM_F_ACK;
+	if (send(sk_route, nlh, nlh->nlmsg_len, 0) < 0)
+		return -1;
+
+	len = recv(sk_route, buf, sizeof(buf), 0);
+	if (len < 0)
+		return -1;
+
+	nlh = (struct nlmsghdr *)buf;
+	if (!NLMSG_OK(nlh, len) || nlh->nlmsg_type != NLMSG_ERROR ||
+	    nlh->nlmsg_seq != seq) {
+		errno = EPROTO;
+		return -1;
+	}
+
+	err = NLMSG_DATA(nlh);
+	if (err->error != 0) {
+		errno = -err->error;
+		return -1;
+	}
+
+	return 0;
+}
+
+/*
+ * Sends the dump request and counts the segments for which `match` returns
+ * true. Returns the number of matched segments, or -1 on failure.
+ */
+static int dump(struct nlmsghdr *nlh, int (*match)(struct nlmsghdr *))
+{
+	struct nlmsghdr *seg;
+	ssize_t len;
+	int count = 0;
+
+	nlh->nlmsg_flags |= NLM_F_DUMP;
+	if (send(sk_route, nlh, nlh->nlmsg_len, 0) < 0)
+		return -1;
+
+	for (;;) {
+		len = recv(sk_route, buf, sizeof(buf), 0);
+		if (len < 0)
+			return -1;
+
+		for (seg = (struct nlmsghdr *)buf; NLMSG_OK(seg, len);
+		     seg = NLMSG_NEXT(seg, len)) {
+			if (seg->nlmsg_seq != seq) {
+				errno = EPROTO;
+				return -1;
+			}
+			if (seg->nlmsg_type == NLMSG_DONE)
+				return count;
+			if (seg->nlmsg_type == NLMSG_ERROR) {
+				errno = -((struct nlmsgerr *)NLMSG_DATA(seg))
+						 ->error;
+				return -1;
+			}
+			if (!(seg->nlmsg_flags & NLM_F_MULTI)) {
+				errno = EPROTO;
+				return -1;
+			}
+			count += match(seg);
+		}
+	}
+}
+
+static struct rtattr *find_attr(struct nlmsghdr *seg, size_t body_len,
+				int type)
+{
+	struct rtattr *rta = (struct rtattr *)((char *)NLMSG_DATA(seg) +
+					       NLMSG_ALIGN(body_len));
+	int len = seg->nlmsg_len - NLMSG_LENGTH(NLMSG_ALIGN(body_len));
+
+	for (; RTA_OK(rta, len); rta = RTA_NEXT(rta, len))
+		if (rta->rta_type == type)
+			return rta;
+
+	return NULL;
+}
+
+static int match_lo_link(struct nlmsghdr *seg)
+{
+	struct ifinfomsg *ifi = NLMSG_DATA(seg);
+	struct rtattr *name = find_attr(seg, sizeof(*ifi), IFLA_IFNAME);
+
+	return seg->nlmsg_type == RTM_NEWLINK && ifi->ifi_index == lo_index &&
+	       (ifi->ifi_flags & IFF_LOOPBACK) && (ifi->ifi_flags & IFF_UP) &&
+	       name != NULL && strcmp(RTA_DATA(name), "lo") == 0;
+}
+
+FN_TEST(get_link)
+{
+	struct ifinfomsg ifi = { .ifi_family = AF_UNSPEC };
+	struct nlmsghdr *nlh;
+
+	nlh = init_request(RTM_GETLINK, 0, &ifi, sizeof(ifi));
+	TEST_RES(dump(nlh, match_lo_link), _ret == 1);
+
+	ifi.ifi_index = lo_index;
+	nlh = init_request(RTM_GETLINK, 0, &ifi, sizeof(ifi));
+	TEST_SUCC(send(sk_route, nlh, nlh->nlmsg_len, 0));
+	TEST_RES(recv(sk_route, buf, sizeof(buf), 0),
+		 NLMSG_OK((struct nlmsghdr *)buf, _ret) &&
+			 match_lo_link((struct nlmsghdr *)buf));
+
+	ifi.ifi_index = 10000;
+	nlh = init_request(RTM_GETLINK, 0, &ifi, sizeof(ifi));
+	TEST_ERRNO(send_request(nlh), ENODEV);
+}
+END_TEST()
+
+static const char *addr_to_match;
+
+static int match_lo_addr(struct nlmsghdr *seg)
+{
+	struct ifaddrmsg *ifa = NLMSG_DATA(seg);
+	struct rtattr *local = find_attr(seg, sizeof(*ifa), IFA_LOCAL);
+	struct in_addr in;
+
+	inet_pton(AF_INET, addr_to_match, &in);
+
+	return seg->nlmsg_type == RTM_NEWADDR && ifa->ifa_family == AF_INET &&
+	       ifa->ifa_index == (unsigned int)lo_index && ifa->ifa_prefixlen == 8 &&
+	       local != NULL && memcmp(RTA_DATA(local), &in, sizeof(in)) == 0;
+}
+
+static struct nlmsghdr *init_addr_request(int type, int flags,
+					  const char *addr)
+{
+	struct ifaddrmsg ifa = {
+		.ifa_family = AF_INET,
+		.ifa_prefixlen = 8,
+		.ifa_scope = RT_SCOPE_HOST,
+		.ifa_index = lo_index,
+	};
+	struct nlmsghdr *nlh;
+
+	nlh = init_request(type, flags, &ifa, sizeof(ifa));
+	add_attr_in_addr(nlh, IFA_LOCAL, addr);
+	add_attr_in_addr(nlh, IFA_ADDRESS, addr);
+
+	return nlh;
+}
+
+FN_TEST(get_addr)
+{
+	struct ifaddrmsg ifa = { .ifa_family = AF_INET };
+	struct nlmsghdr *nlh;
+
+	addr_to_match = "127.0.0.1";
+	nlh = init_request(RTM_GETADDR, 0, &ifa, sizeof(ifa));
+	TEST_RES(dump(nlh, match_lo_addr), _ret == 1);
+}
+END_TEST()
+
+FN_TEST(new_and_del_addr)
+{
+	struct ifaddrmsg ifa = { .ifa_family = AF_INET };
+	struct nlmsghdr *nlh;
+
+	addr_to_match = "127.0.0.2";
+
+	nlh = init_addr_request(RTM_NEWADDR, NLM_F_CREATE | NLM_F_EXCL,
+				addr_to_match);
+	TEST_SUCC(send_request(nlh));
+	nlh = init_addr_request(RTM_NEWADDR, NLM_F_CREATE | NLM_F_EXCL,
+				addr_to_match);
+	TEST_ERRNO(send_request(nlh), EEXIST);
+
+	nlh = init_request(RTM_GETADDR, 0, &ifa, sizeof(ifa));
+	TEST_RES(dump(nlh, match_lo_addr), _ret == 1);
+
+	nlh = init_addr_request(RTM_DELADDR, 0, addr_to_match);
+	TEST_SUCC(send_request(nlh));
+	nlh = init_addr_request(RTM_DELADDR, 0, addr_to_match);
+	TEST_ERRNO(send_request(nlh), EADDRNOTAVAIL);
+
+	nlh = init_request(RTM_GETADDR, 0, &ifa, sizeof(ifa));
+	TEST_RES(dump(nlh, match_lo_addr), _ret == 0);
+}
+END_TEST()
+
+static int match_any(struct nlmsghdr *seg)
+{
+	return seg->nlmsg_type == RTM_NEWROUTE;
+}
+
+FN_TEST(get_and_del_route)
+{
+	struct rtmsg rtm = { .rtm_family = AF_INET };
+	struct nlmsghdr *nlh;
+
+	nlh = init_request(RTM_GETROUTE, 0, &rtm, sizeof(rtm));
+	TEST_RES(dump(nlh, match_any), _ret >= 0);
+
+	rtm.rtm_dst_len = 24;
+	rtm.rtm_table = RT_TABLE_MAIN;
+	nlh = init_request(RTM_DELROUTE, 0, &rtm, sizeof(rtm));
+	add_attr_in_addr(nlh, RTA_DST, "192.168.233.0");
+	add_attr_in_addr(nlh, RTA_GATEWAY, "127.0.0.1");
+	TEST_ERRNO(send_request(nlh), ESRCH);
+}
+END_TEST()
+
+FN_TEST(invalid_request)
+{
+	struct nlmsghdr *nlh;
+
+	// Messages that are too short are silently ignored.
+	nlh = init_request(RTM_GETADDR, 0, NULL, 0);
+	nlh->nlmsg_len = NLMSG_HDRLEN - 1;
+	TEST_RES(send(sk_route, buf, NLMSG_HDRLEN - 1, 0),
+		 _ret == NLMSG_HDRLEN - 1);
+
+	nlh = init_request(0xffff, 0, NULL, 0);
+	TEST_ERRNO(send_request(nlh), EOPNOTSUPP);
+}
+END_TEST()
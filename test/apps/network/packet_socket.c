@@ -0,0 +1,346 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#include <stddef.h>
+#include <unistd.h>
+#include <poll.h>
+#include <sys/mman.h>
+#include <sys/socket.h>
+#include <sys/wait.h>
+#include <netinet/in.h>
+#include <netinet/ip.h>
+#include <netinet/udp.h>
+#include <net/ethernet.h>
+#include <net/if_arp.h>
+#include <linux/if_packet.h>
+#include <arpa/inet.h>
+
+#include "test.h"
+
+#define TEST_PORT 0x1357
+#define TEST_DATA "hello"
+
+static struct sockaddr_in lo_addr;
+static int sk_udp;
+static int lo_index;
+
+FN_SETUP(general)
+{
+	lo_addr.sin_family = AF_INET;
+	lo_addr.sin_port = htons(TEST_PORT);
+	CHECK_WITH(inet_pton(AF_INET, "127.0.0.1", &lo_addr.sin_addr),
+		   _ret == 1);
+
+	sk_udp = CHECK(socket(AF_INET, SOCK_DGRAM, 0));
+	CHECK(bind(sk_udp, (struct sockaddr *)&lo_addr, sizeof(lo_addr)));
+}
+END_SETUP()
+
+static void send_udp(void)
+{
+	char buf[sizeof(TEST_DATA)];
+
+	CHECK_WITH(sendto(sk_udp, TEST_DATA, sizeof(TEST_DATA), 0,
+			  (struct sockaddr *)&lo_addr, sizeof(lo_addr)),
+		   _ret == sizeof(TEST_DATA));
+	CHECK_WITH(recv(sk_udp, buf, sizeof(buf), 0),
+		   _ret == sizeof(TEST_DATA));
+}
+
+/*
+ * Checks whether the IP packet is the UDP packet sent by `send_udp`.
+ */
+static int is_test_packet(const char *packet, size_t len)
+{
+	const struct iphdr *ip = (const struct iphdr *)packet;
+	const struct udphdr *udp;
+
+	if (len < sizeof(*ip) || ip->version != 4 ||
+	    ip->protocol != IPPROTO_UDP || len < ip->ihl * 4 + sizeof(*udp))
+		return 0;
+
+	udp = (const struct udphdr *)(packet + ip->ihl * 4);
+	return udp->dest == htons(TEST_PORT) &&
+	       len == ip->ihl * 4 + sizeof(*udp) + sizeof(TEST_DATA) &&
+	       memcmp(udp + 1, TEST_DATA, sizeof(TEST_DATA)) == 0;
+}
+
+/*
+ * Receives the UDP packet sent by `send_udp`, skipping other frames. Returns
+ * the length of the frame, where the IP packet starts at `ip_offset`.
+ */
+static ssize_t recv_test_frame(int sk, char *buf, size_t len, size_t ip_offset,
+			       struct sockaddr_ll *addr)
+{
+	struct pollfd pfd = { .fd = sk, .events = POLLIN };
+	socklen_t addrlen;
+	ssize_t ret;
+	int i;
+
+	for (i = 0; i < 16; ++i) {
+		// Frames may be captured after the packet is sent.
+		if (poll(&pfd, 1, 1000) < 0)
+			return -1;
+
+		addrlen = sizeof(*addr);
+		ret = recvfrom(sk, buf, len, MSG_DONTWAIT,
+			       (struct sockaddr *)addr, &addrlen);
+		if (ret < 0)
+			return ret;
+
+		if ((size_t)ret >= ip_offset &&
+		    is_test_packet(buf + ip_offset, ret - ip_offset))
+			return ret;
+	}
+
+	errno = ENOMSG;
+	return -1;
+}
+
+FN_TEST(capture_raw)
+{
+	struct sockaddr_ll addr;
+	struct ether_header *eth;
+	char buf[256];
+	int sk;
+
+	sk = TEST_SUCC(socket(AF_PACKET, SOCK_RAW, htons(ETH_P_IP)));
+
+	send_udp();
+
+	// Only the incoming packet is captured, with a fake Ethernet header.
+	// Outgoing packets are captured only by `ETH_P_ALL` sockets.
+	eth = (struct ether_header *)buf;
+	TEST_RES(recv_test_frame(sk, buf, sizeof(buf), sizeof(*eth), &addr),
+		 eth->ether_type == htons(ETHERTYPE_IP) &&
+			 addr.sll_family == AF_PACKET &&
+			 addr.sll_protocol == htons(ETH_P_IP) &&
+			 addr.sll_hatype == ARPHRD_LOOPBACK &&
+			 addr.sll_pkttype == PACKET_HOST &&
+			 addr.sll_halen == ETH_ALEN);
+	lo_index = addr.sll_ifindex;
+
+	TEST_ERRNO(recv(sk, buf, sizeof(buf), MSG_DONTWAIT), EAGAIN);
+
+	TEST_SUCC(close(sk));
+}
+END_TEST()
+
+FN_TEST(capture_dgram)
+{
+	struct sockaddr_ll addr;
+	socklen_t addrlen;
+	char buf[256];
+	int sk;
+
+	sk = TEST_SUCC(socket(AF_PACKET, SOCK_DGRAM, 0));
+
+	// A socket with a zero protocol does not capture any frames.
+	send_udp();
+	TEST_ERRNO(recv(sk, buf, sizeof(buf), MSG_DONTWAIT), EAGAIN);
+
+	memset(&addr, 0, sizeof(addr));
+	addr.sll_family = AF_PACKET;
+	addr.sll_protocol = htons(ETH_P_IP);
+	addr.sll_ifindex = 0x7fffffff;
+	TEST_ERRNO(bind(sk, (struct sockaddr *)&addr, sizeof(addr)), ENODEV);
+	addr.sll_ifindex = lo_index;
+	TEST_SUCC(bind(sk, (struct sockaddr *)&addr, sizeof(addr)));
+
+	memset(&addr, 0, sizeof(addr));
+	addrlen = sizeof(addr);
+	TEST_RES(getsockname(sk, (struct sockaddr *)&addr, &addrlen),
+		 addrlen == offsetof(struct sockaddr_ll, sll_addr) + ETH_ALEN &&
+			 addr.sll_protocol == htons(ETH_P_IP) &&
+			 addr.sll_ifindex == lo_index &&
+			 addr.sll_hatype == ARPHRD_LOOPBACK);
+
+	// `SOCK_DGRAM` sockets do not receive the link-layer headers.
+	send_udp();
+	TEST_RES(recv_test_frame(sk, buf, sizeof(buf), 0, &addr),
+		 addr.sll_ifindex == lo_index &&
+			 addr.sll_pkttype == PACKET_HOST);
+
+	TEST_SUCC(close(sk));
+}
+END_TEST()
+
+static unsigned short checksum(const void *data, size_t len)
+{
+	const unsigned char *bytes = data;
+	unsigned int sum = 0;
+	size_t i;
+
+	for (i = 0; i + 1 < len; i += 2)
+		sum += (bytes[i] << 8) | bytes[i + 1];
+
+	while (sum >> 16)
+		sum = (sum & 0xffff) + (sum >> 16);
+
+	return htons(~sum);
+}
+
+FN_TEST(send_raw)
+{
+	struct {
+		struct ether_header eth;
+		struct iphdr ip;
+		struct udphdr udp;
+		char data[sizeof(TEST_DATA)];
+	} __attribute__((packed)) frame;
+	struct sockaddr_ll addr;
+	char buf[256];
+	int sk, sk_cap;
+
+	sk = TEST_SUCC(socket(AF_PACKET, SOCK_RAW, 0));
+	sk_cap = TEST_SUCC(socket(AF_PACKET, SOCK_DGRAM, htons(ETH_P_IP)));
+
+	memset(&frame, 0, sizeof(frame));
+	frame.eth.ether_type = htons(ETHERTYPE_IP);
+	frame.ip.version = 4;
+	frame.ip.ihl = sizeof(frame.ip) / 4;
+	frame.ip.tot_len = htons(sizeof(frame) - sizeof(frame.eth));
+	frame.ip.ttl = 64;
+	frame.ip.protocol = IPPROTO_UDP;
+	frame.ip.saddr = lo_addr.sin_addr.s_addr;
+	frame.ip.daddr = lo_addr.sin_addr.s_addr;
+	frame.ip.check = checksum(&frame.ip, sizeof(frame.ip));
+	frame.udp.source = htons(TEST_PORT);
+	frame.udp.dest = htons(TEST_PORT);
+	frame.udp.len = htons(sizeof(frame.udp) + sizeof(frame.data));
+	memcpy(frame.data, TEST_DATA, sizeof(TEST_DATA));
+
+	// The socket is not bound, so the iface must be specified.
+	TEST_ERRNO(send(sk, &frame, sizeof(frame), 0), ENXIO);
+
+	memset(&addr, 0, sizeof(addr));
+	addr.sll_family = AF_PACKET;
+	addr.sll_ifindex = lo_index;
+	TEST_ERRNO(sendto(sk, &frame, sizeof(frame.eth) - 1, 0,
+			  (struct sockaddr *)&addr, sizeof(addr)),
+		   EINVAL);
+	TEST_RES(sendto(sk, &frame, sizeof(frame), 0, (struct sockaddr *)&addr,
+			sizeof(addr)),
+		 _ret == sizeof(frame));
+
+	// The frame is received by the loopback iface.
+	TEST_RES(recv_test_frame(sk_cap, buf, sizeof(buf), 0, &addr),
+		 addr.sll_ifindex == lo_index &&
+			 addr.sll_pkttype == PACKET_HOST);
+
+	TEST_SUCC(close(sk_cap));
+	TEST_SUCC(close(sk));
+}
+END_TEST()
+
+#define RING_BLOCK_SIZE 4096
+#define RING_BLOCK_NR 2
+#define RING_FRAME_SIZE 2048
+#define RING_FRAME_NR 4
+
+FN_TEST(rx_ring)
+{
+	struct tpacket_req req;
+	struct tpacket2_hdr *hdr;
+	struct sockaddr_ll *addr;
+	struct sockaddr_ll bind_addr;
+	struct pollfd pfd;
+	char *ring, *frame;
+	int sk, val, i;
+	socklen_t len;
+
+	sk = TEST_SUCC(socket(AF_PACKET, SOCK_RAW, htons(ETH_P_ALL)));
+
+	memset(&bind_addr, 0, sizeof(bind_addr));
+	bind_addr.sll_family = AF_PACKET;
+	bind_addr.sll_ifindex = lo_index;
+	TEST_SUCC(bind(sk, (struct sockaddr *)&bind_addr, sizeof(bind_addr)));
+
+	val = TPACKET_V2;
+	len = sizeof(val);
+	TEST_RES(getsockopt(sk, SOL_PACKET, PACKET_HDRLEN, &val, &len),
+		 len == sizeof(val) &&
+			 val == sizeof(struct tpacket2_hdr));
+
+	val = TPACKET_V2;
+	TEST_SUCC(setsockopt(sk, SOL_PACKET, PACKET_VERSION, &val, sizeof(val)));
+
+	// The number of frames must match the number of blocks.
+	req.tp_block_size = RING_BLOCK_SIZE;
+	req.tp_block_nr = RING_BLOCK_NR;
+	req.tp_frame_size = RING_FRAME_SIZE;
+	req.tp_frame_nr = RING_FRAME_NR + 1;
+	TEST_ERRNO(setsockopt(sk, SOL_PACKET, PACKET_RX_RING, &req,
+			      sizeof(req)),
+		   EINVAL);
+	req.tp_frame_nr = RING_FRAME_NR;
+	TEST_SUCC(setsockopt(sk, SOL_PACKET, PACKET_RX_RING, &req, sizeof(req)));
+
+	// The layout cannot be changed after the ring is set up.
+	TEST_ERRNO(setsockopt(sk, SOL_PACKET, PACKET_VERSION, &val,
+			      sizeof(val)),
+		   EBUSY);
+	TEST_ERRNO(setsockopt(sk, SOL_PACKET, PACKET_RX_RING, &req,
+			      sizeof(req)),
+		   EBUSY);
+
+	ring = (char *)TEST_RES((long)mmap(NULL, RING_BLOCK_SIZE * RING_BLOCK_NR,
+					   PROT_READ | PROT_WRITE, MAP_SHARED,
+					   sk, 0),
+				_ret != (long)MAP_FAILED);
+
+	send_udp();
+
+	pfd.fd = sk;
+	pfd.events = POLLIN;
+	TEST_RES(poll(&pfd, 1, 1000), _ret == 1 && pfd.revents == POLLIN);
+
+	// The outgoing and incoming packets are both in the ring.
+	for (i = 0; i < 2; ++i) {
+		frame = ring + i * RING_FRAME_SIZE;
+		hdr = (struct tpacket2_hdr *)frame;
+		addr = (struct sockaddr_ll *)(frame +
+					      TPACKET_ALIGN(sizeof(*hdr)));
+		TEST_RES(hdr->tp_status,
+			 (hdr->tp_status & TP_STATUS_USER) &&
+				 hdr->tp_net == hdr->tp_mac + ETH_HLEN &&
+				 hdr->tp_snaplen == hdr->tp_len &&
+				 is_test_packet(frame + hdr->tp_net,
+						hdr->tp_len - ETH_HLEN) &&
+				 addr->sll_ifindex == lo_index &&
+				 addr->sll_pkttype ==
+					 (i == 0 ? PACKET_OUTGOING :
+						   PACKET_HOST));
+		hdr->tp_status = TP_STATUS_KERNEL;
+	}
+
+	TEST_RES(poll(&pfd, 1, 0), _ret == 0);
+
+	TEST_SUCC(munmap(ring, RING_BLOCK_SIZE * RING_BLOCK_NR));
+	TEST_SUCC(close(sk));
+}
+END_TEST()
+
+FN_TEST(permission)
+{
+	int status;
+	pid_t pid;
+
+	pid = TEST_SUCC(fork());
+	if (pid == 0) {
+		// Dropping the root privilege also drops `CAP_NET_RAW`.
+		CHECK(setuid(65534));
+		CHECK_WITH(socket(AF_PACKET, SOCK_RAW, htons(ETH_P_ALL)),
+			   _ret < 0 && errno == EPERM);
+		exit(0);
+	}
+
+	TEST_RES(waitpid(pid, &status, 0),
+		 _ret == pid && WIFEXITED(status) && WEXITSTATUS(status) == 0);
+}
+END_TEST()
+
+FN_TEST(invalid_socket)
+{
+	TEST_ERRNO(socket(AF_PACKET, SOCK_STREAM, 0), ESOCKTNOSUPPORT);
+}
+END_TEST()
@@ -0,0 +1,274 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#include <unistd.h>
+#include <sys/socket.h>
+#include <netinet/in.h>
+#include <netinet/ip.h>
+#include <netinet/ip_icmp.h>
+#include <netinet/icmp6.h>
+#include <arpa/inet.h>
+
+#include "test.h"
+
+static struct sockaddr_in lo_addr;
+static struct sockaddr_in6 lo6_addr;
+
+#define PING_IDENT 0x1234
+#define PING_SEQ 0x5678
+#define PING_DATA "hello"
+
+FN_SETUP(general)
+{
+	lo_addr.sin_family = AF_INET;
+	CHECK_WITH(inet_pton(AF_INET, "127.0.0.1", &lo_addr.sin_addr),
+		   _ret == 1);
+
+	lo6_addr.sin6_family = AF_INET6;
+	lo6_addr.sin6_addr = in6addr_loopback;
+}
+END_SETUP()
+
+static unsigned short checksum(const void *data, size_t len)
+{
+	const unsigned char *bytes = data;
+	unsigned int sum = 0;
+	size_t i;
+
+	for (i = 0; i + 1 < len; i += 2)
+		sum += (bytes[i] << 8) | bytes[i + 1];
+	if (len % 2)
+		sum += bytes[len - 1] << 8;
+
+	while (sum >> 16)
+		sum = (sum & 0xffff) + (sum >> 16);
+
+	return htons(~sum);
+}
+
+struct echo_message {
+	struct icmphdr hdr;
+	char data[sizeof(PING_DATA)];
+};
+
+static void init_echo_request(struct echo_message *msg, int type)
+{
+	memset(msg, 0, sizeof(*msg));
+	msg->hdr.type = type;
+	msg->hdr.un.echo.id = htons(PING_IDENT);
+	msg->hdr.un.echo.sequence = htons(PING_SEQ);
+	memcpy(msg->data, PING_DATA, sizeof(PING_DATA));
+	msg->hdr.checksum = checksum(msg, sizeof(*msg));
+}
+
+FN_TEST(ping)
+{
+	struct echo_message msg;
+	struct sockaddr_in addr;
+	socklen_t addrlen;
+	int sk;
+	unsigned short ident;
+
+	sk = TEST_SUCC(socket(AF_INET, SOCK_DGRAM, IPPROTO_ICMP));
+
+	init_echo_request(&msg, ICMP_ECHO);
+	TEST_RES(sendto(sk, &msg, sizeof(msg), 0, (struct sockaddr *)&lo_addr,
+			sizeof(lo_addr)),
+		 _ret == sizeof(msg));
+
+	// The identifier is allocated automatically and acts as the port.
+	addrlen = sizeof(addr);
+	TEST_RES(getsockname(sk, (struct sockaddr *)&addr, &addrlen),
+		 addrlen == sizeof(addr) && addr.sin_port != 0);
+	ident = addr.sin_port;
+
+	memset(&msg, 0, sizeof(msg));
+	addrlen = sizeof(addr);
+	TEST_RES(recvfrom(sk, &msg, sizeof(msg), 0, (struct sockaddr *)&addr,
+			  &addrlen),
+		 _ret == sizeof(msg) && msg.hdr.type == ICMP_ECHOREPLY &&
+			 msg.hdr.un.echo.id == ident &&
+			 msg.hdr.un.echo.sequence == htons(PING_SEQ) &&
+			 strcmp(msg.data, PING_DATA) == 0 &&
+			 addrlen == sizeof(addr) &&
+			 addr.sin_addr.s_addr == lo_addr.sin_addr.s_addr &&
+			 addr.sin_port == 0);
+
+	// Only echo requests can be sent.
+	init_echo_request(&msg, ICMP_ECHOREPLY);
+	TEST_ERRNO(sendto(sk, &msg, sizeof(msg), 0,
+			  (struct sockaddr *)&lo_addr, sizeof(lo_addr)),
+		   EINVAL);
+	init_echo_request(&msg, ICMP_ECHO);
+	TEST_ERRNO(sendto(sk, &msg, 4, 0, (struct sockaddr *)&lo_addr,
+			  sizeof(lo_addr)),
+		   EINVAL);
+
+	TEST_SUCC(close(sk));
+}
+END_TEST()
+
+FN_TEST(ping_bind)
+{
+	struct sockaddr_in addr;
+	socklen_t addrlen;
+	int sk;
+
+	sk = TEST_SUCC(socket(AF_INET, SOCK_DGRAM, IPPROTO_ICMP));
+
+	lo_addr.sin_port = htons(PING_IDENT);
+	TEST_SUCC(bind(sk, (struct sockaddr *)&lo_addr, sizeof(lo_addr)));
+	TEST_ERRNO(bind(sk, (struct sockaddr *)&lo_addr, sizeof(lo_addr)),
+		   EINVAL);
+	lo_addr.sin_port = 0;
+
+	addrlen = sizeof(addr);
+	TEST_RES(getsockname(sk, (struct sockaddr *)&addr, &addrlen),
+		 addrlen == sizeof(addr) &&
+			 addr.sin_addr.s_addr == lo_addr.sin_addr.s_addr &&
+			 addr.sin_port == htons(PING_IDENT));
+
+	TEST_SUCC(close(sk));
+}
+END_TEST()
+
+FN_TEST(ping6)
+{
+	struct icmp6_hdr *hdr;
+	char buf[sizeof(*hdr) + sizeof(PING_DATA)];
+	struct sockaddr_in6 addr;
+	socklen_t addrlen;
+	int sk;
+
+	sk = TEST_SUCC(socket(AF_INET6, SOCK_DGRAM, IPPROTO_ICMPV6));
+
+	// The checksum is computed by the kernel.
+	memset(buf, 0, sizeof(buf));
+	hdr = (struct icmp6_hdr *)buf;
+	hdr->icmp6_type = ICMP6_ECHO_REQUEST;
+	hdr->icmp6_seq = htons(PING_SEQ);
+	memcpy(buf + sizeof(*hdr), PING_DATA, sizeof(PING_DATA));
+	TEST_RES(sendto(sk, buf, sizeof(buf), 0, (struct sockaddr *)&lo6_addr,
+			sizeof(lo6_addr)),
+		 _ret == sizeof(buf));
+
+	memset(buf, 0, sizeof(buf));
+	addrlen = sizeof(addr);
+	TEST_RES(recvfrom(sk, buf, sizeof(buf), 0, (struct sockaddr *)&addr,
+			  &addrlen),
+		 _ret == sizeof(buf) && hdr->icmp6_type == ICMP6_ECHO_REPLY &&
+			 hdr->icmp6_seq == htons(PING_SEQ) &&
+			 strcmp(buf + sizeof(*hdr), PING_DATA) == 0 &&
+			 addrlen == sizeof(addr) &&
+			 IN6_IS_ADDR_LOOPBACK(&addr.sin6_addr));
+
+	TEST_SUCC(close(sk));
+}
+END_TEST()
+
+/*
+ * Receives an echo reply from the raw ICMP socket, skipping other messages
+ * (e.g., the echo request itself). Returns the length of the reply, including
+ * the IP header.
+ */
+static ssize_t recv_echo_reply(int sk, char *buf, size_t len)
+{
+	struct iphdr *ip = (struct iphdr *)buf;
+	struct icmphdr *icmp;
+	ssize_t ret;
+	int i;
+
+	for (i = 0; i < 4; ++i) {
+		ret = recv(sk, buf, len, 0);
+		if (ret < 0)
+			return ret;
+
+		icmp = (struct icmphdr *)(buf + ip->ihl * 4);
+		if (icmp->type == ICMP_ECHOREPLY &&
+		    icmp->un.echo.id == htons(PING_IDENT))
+			return ret;
+	}
+
+	errno = ENOMSG;
+	return -1;
+}
+
+FN_TEST(raw_icmp)
+{
+	struct echo_message msg;
+	struct iphdr *ip;
+	char buf[128];
+	struct sockaddr_in addr;
+	socklen_t addrlen;
+	int sk;
+
+	sk = TEST_SUCC(socket(AF_INET, SOCK_RAW, IPPROTO_ICMP));
+
+	// The port of a raw socket is its protocol number.
+	addrlen = sizeof(addr);
+	TEST_RES(getsockname(sk, (struct sockaddr *)&addr, &addrlen),
+		 addrlen == sizeof(addr) && addr.sin_port == htons(IPPROTO_ICMP));
+
+	init_echo_request(&msg, ICMP_ECHO);
+	TEST_RES(sendto(sk, &msg, sizeof(msg), 0, (struct sockaddr *)&lo_addr,
+			sizeof(lo_addr)),
+		 _ret == sizeof(msg));
+
+	// IPv4 raw sockets receive the IP header.
+	ip = (struct iphdr *)buf;
+	TEST_RES(recv_echo_reply(sk, buf, sizeof(buf)),
+		 _ret == (ssize_t)(ip->ihl * 4 + sizeof(msg)) &&
+			 ip->version == 4 && ip->protocol == IPPROTO_ICMP &&
+			 ip->saddr == lo_addr.sin_addr.s_addr &&
+			 memcmp(buf + ip->ihl * 4 + sizeof(msg.hdr), PING_DATA,
+				sizeof(PING_DATA)) == 0);
+
+	TEST_SUCC(close(sk));
+}
+END_TEST()
+
+FN_TEST(raw_hdrincl)
+{
+	struct {
+		struct iphdr ip;
+		struct echo_message msg;
+	} pkt;
+	char buf[128];
+	int sk, sk_icmp;
+	int val;
+	socklen_t len;
+
+	sk = TEST_SUCC(socket(AF_INET, SOCK_RAW, IPPROTO_RAW));
+	sk_icmp = TEST_SUCC(socket(AF_INET, SOCK_RAW, IPPROTO_ICMP));
+
+	// `IP_HDRINCL` is implied by `IPPROTO_RAW`.
+	len = sizeof(val);
+	TEST_RES(getsockopt(sk, IPPROTO_IP, IP_HDRINCL, &val, &len),
+		 len == sizeof(val) && val == 1);
+
+	memset(&pkt, 0, sizeof(pkt));
+	pkt.ip.version = 4;
+	pkt.ip.ihl = sizeof(pkt.ip) / 4;
+	pkt.ip.tot_len = htons(sizeof(pkt));
+	pkt.ip.ttl = 64;
+	pkt.ip.protocol = IPPROTO_ICMP;
+	pkt.ip.saddr = lo_addr.sin_addr.s_addr;
+	pkt.ip.daddr = lo_addr.sin_addr.s_addr;
+	init_echo_request(&pkt.msg, ICMP_ECHO);
+	TEST_RES(sendto(sk, &pkt, sizeof(pkt), 0, (struct sockaddr *)&lo_addr,
+			sizeof(lo_addr)),
+		 _ret == sizeof(pkt));
+
+	TEST_RES(recv_echo_reply(sk_icmp, buf, sizeof(buf)),
+		 _ret == sizeof(pkt));
+
+	TEST_SUCC(close(sk_icmp));
+	TEST_SUCC(close(sk));
+}
+END_TEST()
+
+FN_TEST(invalid_socket)
+{
+	TEST_ERRNO(socket(AF_INET, SOCK_RAW, 0), EPROTONOSUPPORT);
+	TEST_ERRNO(socket(AF_INET6, SOCK_RAW, 0), EPROTONOSUPPORT);
+}
+END_TEST()
@@ -1,8 +1,10 @@
 // SPDX-License-Identifier: MPL-2.0
 
+#include <poll.h>
 #include <stdio.h>
 #include <stdlib.h>
 #include <sys/socket.h>
+#include <sys/time.h>
 #include <netinet/in.h>
 #include <netinet/tcp.h>
 #include <unistd.h>
@@ -84,6 +86,15 @@ FN_TEST(buffer_size)
 	TEST_RES(getsockopt(sk_unbound, SOL_SOCKET, SO_SNDBUF, &sendbuf,
 			    &sendbuf_len),
 		 sendbuf_len == sizeof(sendbuf));
+
+	// The value is doubled when it is set
+	int recvbuf = 0x4000;
+	socklen_t recvbuf_len = sizeof(recvbuf);
+	CHECK(setsockopt(sk_unbound, SOL_SOCKET, SO_RCVBUF, &recvbuf,
+			 recvbuf_len));
+	TEST_RES(getsockopt(sk_unbound, SOL_SOCKET, SO_RCVBUF, &recvbuf,
+			    &recvbuf_len),
+		 recvbuf == 0x8000 && recvbuf_len == sizeof(recvbuf));
 }
 END_TEST()
 
@@ -279,6 +290,109 @@ FN_TEST(keepidle)
 }
 END_TEST()
 
+FN_TEST(congestion)
+{
+	char name[16];
+	socklen_t name_len = sizeof(name);
+
+	// 1. Check the default value, which depends on the kernel configuration
+	TEST_RES(getsockopt(sk_unbound, IPPROTO_TCP, TCP_CONGESTION, name,
+			    &name_len),
+		 name_len > 0 && strlen(name) > 0);
+
+	// 2. Switch the algorithm
+	CHECK(setsockopt(sk_connected, IPPROTO_TCP, TCP_CONGESTION, "reno",
+			 strlen("reno")));
+	name_len = sizeof(name);
+	TEST_RES(getsockopt(sk_connected, IPPROTO_TCP, TCP_CONGESTION, name,
+			    &name_len),
+		 strcmp(name, "reno") == 0);
+	TEST_RES(write(sk_connected, "hello", 5), _ret == 5);
+	TEST_RES(read(sk_accepted, name, sizeof(name)), _ret == 5);
+
+	// 3. Unknown algorithms are rejected
+	TEST_ERRNO(setsockopt(sk_connected, IPPROTO_TCP, TCP_CONGESTION,
+			      "unknown", strlen("unknown")),
+		   ENOENT);
+
+	CHECK(setsockopt(sk_connected, IPPROTO_TCP, TCP_CONGESTION, "cubic",
+			 strlen("cubic")));
+}
+END_TEST()
+
+FN_TEST(large_window)
+{
+	int sk_listen2, sk_client, sk_server;
+	struct sockaddr_in addr = listen_addr;
+	int bufsize = 212992;
+	static char send_buf[1024 * 1024], recv_buf[1024 * 1024];
+	struct pollfd pfds[2];
+	size_t sent = 0, recvd = 0;
+	long res;
+
+	addr.sin_port = htons(0x1243);
+
+	// 1. Request larger buffers so that the window scale factor is larger
+	sk_listen2 = TEST_SUCC(socket(AF_INET, SOCK_STREAM, 0));
+	TEST_SUCC(setsockopt(sk_listen2, SOL_SOCKET, SO_RCVBUF, &bufsize,
+			     sizeof(bufsize)));
+	TEST_SUCC(bind(sk_listen2, (struct sockaddr *)&addr, sizeof(addr)));
+	TEST_SUCC(listen(sk_listen2, 1));
+
+	sk_client = TEST_SUCC(socket(AF_INET, SOCK_STREAM, 0));
+	TEST_SUCC(setsockopt(sk_client, SOL_SOCKET, SO_SNDBUF, &bufsize,
+			     sizeof(bufsize)));
+	TEST_SUCC(connect(sk_client, (struct sockaddr *)&addr, sizeof(addr)));
+	sk_server = TEST_SUCC(accept(sk_listen2, NULL, NULL));
+
+	pfds[0].fd = sk_client;
+	pfds[1].fd = sk_server;
+
+	// 2. Fill the window without receiving. Without window scaling, the
+	// receiving end could not hold more than 64 KiB of pending data.
+	memset(send_buf, 'a', sizeof(send_buf));
+	pfds[0].events = POLLOUT;
+	while (sent < sizeof(send_buf) && poll(&pfds[0], 1, 100) > 0) {
+		res = send(sk_client, send_buf + sent, sizeof(send_buf) - sent,
+			   MSG_DONTWAIT);
+		if (res <= 0)
+			break;
+		sent += res;
+	}
+	pfds[1].events = POLLIN;
+	TEST_RES(poll(&pfds[1], 1, 1000), _ret == 1);
+	recvd = TEST_RES(recv(sk_server, recv_buf, sizeof(recv_buf),
+			      MSG_DONTWAIT),
+			 _ret > 65536);
+
+	// 3. Transfer the rest of the data, which must not stall
+	while (recvd < sizeof(recv_buf)) {
+		pfds[0].events = sent < sizeof(send_buf) ? POLLOUT : 0;
+		if (poll(pfds, 2, 1000) <= 0)
+			break;
+
+		if (pfds[0].revents & POLLOUT) {
+			res = send(sk_client, send_buf + sent,
+				   sizeof(send_buf) - sent, MSG_DONTWAIT);
+			if (res > 0)
+				sent += res;
+		}
+		if (pfds[1].revents & POLLIN) {
+			res = recv(sk_server, recv_buf + recvd,
+				   sizeof(recv_buf) - recvd, MSG_DONTWAIT);
+			if (res > 0)
+				recvd += res;
+		}
+	}
+	TEST_RES(recvd, _ret == sizeof(recv_buf));
+	TEST_RES(memcmp(send_buf, recv_buf, sizeof(recv_buf)), _ret == 0);
+
+	TEST_SUCC(close(sk_server));
+	TEST_SUCC(close(sk_client));
+	TEST_SUCC(close(sk_listen2));
+}
+END_TEST()
+
 FN_TEST(ip_tos)
 {
 	int tos;
@@ -360,3 +474,66 @@ FN_TEST(ip_hdrincl)
 		   ENOPROTOOPT);
 }
 END_TEST()
+
+FN_TEST(timestamp)
+{
+	int option;
+	socklen_t option_len = sizeof(option);
+
+	// 1. Check default values
+	TEST_RES(getsockopt(sk_accepted, SOL_SOCKET, SO_TIMESTAMP, &option,
+			    &option_len),
+		 option == 0);
+	TEST_RES(getsockopt(sk_accepted, SOL_SOCKET, SO_TIMESTAMPNS, &option,
+			    &option_len),
+		 option == 0);
+
+	// 2. `SO_TIMESTAMPNS` overrides `SO_TIMESTAMP`
+	option = 1;
+	CHECK(setsockopt(sk_accepted, SOL_SOCKET, SO_TIMESTAMP, &option,
+			 option_len));
+	CHECK(setsockopt(sk_accepted, SOL_SOCKET, SO_TIMESTAMPNS, &option,
+			 option_len));
+	TEST_RES(getsockopt(sk_accepted, SOL_SOCKET, SO_TIMESTAMP, &option,
+			    &option_len),
+		 option == 0);
+	TEST_RES(getsockopt(sk_accepted, SOL_SOCKET, SO_TIMESTAMPNS, &option,
+			    &option_len),
+		 option == 1);
+
+	// 3. Receive the timestamp
+	option = 1;
+	CHECK(setsockopt(sk_accepted, SOL_SOCKET, SO_TIMESTAMP, &option,
+			 option_len));
+
+	TEST_RES(write(sk_connected, "hello", 5), _ret == 5);
+
+	char buf[5];
+	struct iovec iov = { .iov_base = buf, .iov_len = sizeof(buf) };
+	char control[CMSG_SPACE(sizeof(struct timeval))];
+	struct msghdr msg = {
+		.msg_iov = &iov,
+		.msg_iovlen = 1,
+		.msg_control = control,
+		.msg_controllen = sizeof(control),
+	};
+	struct cmsghdr *cmsg;
+
+	TEST_RES(recvmsg(sk_accepted, &msg, 0),
+		 _ret == 5 && (cmsg = CMSG_FIRSTHDR(&msg)) != NULL &&
+			 cmsg->cmsg_level == SOL_SOCKET &&
+			 cmsg->cmsg_type == SCM_TIMESTAMP &&
+			 cmsg->cmsg_len == CMSG_LEN(sizeof(struct timeval)) &&
+			 (msg.msg_flags & MSG_CTRUNC) == 0);
+
+	// 4. Truncate the timestamp
+	TEST_RES(write(sk_connected, "hello", 5), _ret == 5);
+	msg.msg_controllen = CMSG_LEN(sizeof(struct timeval)) - 1;
+	TEST_RES(recvmsg(sk_accepted, &msg, 0),
+		 _ret == 5 && (msg.msg_flags & MSG_CTRUNC) != 0);
+
+	option = 0;
+	CHECK(setsockopt(sk_accepted, SOL_SOCKET, SO_TIMESTAMP, &option,
+			 option_len));
+}
+END_TEST()
@@ -0,0 +1,602 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <arpa/inet.h>
+#include <fcntl.h>
+#include <linux/if_tun.h>
+#include <linux/netlink.h>
+#include <linux/rtnetlink.h>
+#include <linux/virtio_net.h>
+#include <net/ethernet.h>
+#include <net/if.h>
+#include <netinet/in.h>
+#include <netinet/ip.h>
+#include <netinet/ip_icmp.h>
+#include <netinet/udp.h>
+#include <poll.h>
+#include <sys/ioctl.h>
+#include <sys/socket.h>
+#include <unistd.h>
+
+#include "test.h"
+
+#define BUF_SIZE 8192
+#define TEST_PORT 0x1a2b
+#define TEST_DATA "hello"
+#define TEST_ETHERTYPE 0x88b5
+
+static int sk_route;
+static unsigned int seq;
+static char buf[BUF_SIZE];
+
+FN_SETUP(socket)
+{
+	sk_route = CHECK(socket(AF_NETLINK, SOCK_RAW, NETLINK_ROUTE));
+}
+END_SETUP()
+
+static struct nlmsghdr *init_link_request(int type, int flags, int index)
+{
+	struct nlmsghdr *nlh = (struct nlmsghdr *)buf;
+	struct ifinfomsg ifi = { .ifi_family = AF_UNSPEC, .ifi_index = index };
+
+	memset(buf, 0, sizeof(buf));
+	nlh->nlmsg_len = NLMSG_LENGTH(sizeof(ifi));
+	nlh->nlmsg_type = type;
+	nlh->nlmsg_flags = NLM_F_REQUEST | flags;
+	nlh->nlmsg_seq = ++seq;
+	memcpy(NLMSG_DATA(nlh), &ifi, sizeof(ifi));
+
+	return nlh;
+}
+
+static void add_attr(struct nlmsghdr *nlh, int type, const void *data,
+		     size_t len)
+{
+	struct rtattr *rta =
+		(struct rtattr *)((char *)nlh + NLMSG_ALIGN(nlh->nlmsg_len));
+
+	rta->rta_type = type;
+	rta->rta_len = RTA_LENGTH(len);
+	memcpy(RTA_DATA(rta), data, len);
+	nlh->nlmsg_len = NLMSG_ALIGN(nlh->nlmsg_len) + RTA_ALIGN(rta->rta_len);
+}
+
+static void add_attr_str(struct nlmsghdr *nlh, int type, const char *str)
+{
+	add_attr(nlh, type, str, strlen(str) + 1);
+}
+
+static void add_attr_u32(struct nlmsghdr *nlh, int type, unsigned int value)
+{
+	add_attr(nlh, type, &value, sizeof(value));
+}
+
+/*
+ * Adds the `IFLA_LINKINFO` attribute, which contains the nested
+ * `IFLA_INFO_KIND` attribute.
+ */
+static void add_link_kind(struct nlmsghdr *nlh, const char *kind)
+{
+	struct rtattr *link_info =
+		(struct rtattr *)((char *)nlh + NLMSG_ALIGN(nlh->nlmsg_len));
+
+	link_info->rta_type = IFLA_LINKINFO;
+	nlh->nlmsg_len = NLMSG_ALIGN(nlh->nlmsg_len) + RTA_LENGTH(0);
+	add_attr_str(nlh, IFLA_INFO_KIND, kind);
+	link_info->rta_len = (char *)nlh + nlh->nlmsg_len - (char *)link_info;
+}
+
+/*
+ * Sends the request and waits for the acknowledgment. Returns zero on success
+ * and sets errno to the error code on failure.
+ */
+static int send_request(struct nlmsghdr *nlh)
+{
+	struct nlmsgerr *err;
+	ssize_t len;
+
+	nlh->nlmsg_flags |= NLM_F_ACK;
+	if (send(sk_route, nlh, nlh->nlmsg_len, 0) < 0)
+		return -1;
+
+	len = recv(sk_route, buf, sizeof(buf), 0);
+	if (len < 0)
+		return -1;
+
+	nlh = (struct nlmsghdr *)buf;
+	if (!NLMSG_OK(nlh, len) || nlh->nlmsg_type != NLMSG_ERROR ||
+	    nlh->nlmsg_seq != seq) {
+		errno = EPROTO;
+		return -1;
+	}
+
+	err = NLMSG_DATA(nlh);
+	if (err->error != 0) {
+		errno = -err->error;
+		return -1;
+	}
+
+	return 0;
+}
+
+static struct rtattr *find_attr(struct nlmsghdr *seg, int type)
+{
+	struct rtattr *rta = IFLA_RTA(NLMSG_DATA(seg));
+	int len = IFLA_PAYLOAD(seg);
+
+	for (; RTA_OK(rta, len); rta = RTA_NEXT(rta, len))
+		if (rta->rta_type == type)
+			return rta;
+
+	return NULL;
+}
+
+/*
+ * Gets the link named `name`. Returns the link index if `attr_type` is zero,
+ * or the value of the 32-bit attribute `attr_type` (zero if it is missing).
+ * Sets errno to the error code on failure.
+ */
+static int get_link(const char *name, int attr_type)
+{
+	struct nlmsghdr *nlh;
+	struct rtattr *rta;
+	ssize_t len;
+
+	nlh = init_link_request(RTM_GETLINK, 0, 0);
+	add_attr_str(nlh, IFLA_IFNAME, name);
+	if (send(sk_route, nlh, nlh->nlmsg_len, 0) < 0)
+		return -1;
+
+	len = recv(sk_route, buf, sizeof(buf), 0);
+	if (len < 0)
+		return -1;
+
+	nlh = (struct nlmsghdr *)buf;
+	if (!NLMSG_OK(nlh, len) || nlh->nlmsg_seq != seq) {
+		errno = EPROTO;
+		return -1;
+	}
+	if (nlh->nlmsg_type == NLMSG_ERROR) {
+		errno = -((struct nlmsgerr *)NLMSG_DATA(nlh))->error;
+		return -1;
+	}
+	if (nlh->nlmsg_type != RTM_NEWLINK) {
+		errno = EPROTO;
+		return -1;
+	}
+
+	if (attr_type == 0)
+		return ((struct ifinfomsg *)NLMSG_DATA(nlh))->ifi_index;
+
+	rta = find_attr(nlh, attr_type);
+	return rta == NULL ? 0 : *(int *)RTA_DATA(rta);
+}
+
+static int set_master(const char *name, int master)
+{
+	struct nlmsghdr *nlh;
+
+	nlh = init_link_request(RTM_SETLINK, 0, 0);
+	add_attr_str(nlh, IFLA_IFNAME, name);
+	add_attr_u32(nlh, IFLA_MASTER, master);
+
+	return send_request(nlh);
+}
+
+static int del_link(const char *name)
+{
+	struct nlmsghdr *nlh;
+
+	nlh = init_link_request(RTM_DELLINK, 0, 0);
+	add_attr_str(nlh, IFLA_IFNAME, name);
+
+	return send_request(nlh);
+}
+
+/*
+ * Opens `/dev/net/tun` and attaches to the device named `name`. The actual
+ * name of the device is stored in `name`.
+ */
+static int open_tun(char *name, int flags)
+{
+	struct ifreq ifr;
+	int fd;
+
+	fd = open("/dev/net/tun", O_RDWR | O_NONBLOCK);
+	if (fd < 0)
+		return -1;
+
+	memset(&ifr, 0, sizeof(ifr));
+	strncpy(ifr.ifr_name, name, IFNAMSIZ - 1);
+	ifr.ifr_flags = flags;
+	if (ioctl(fd, TUNSETIFF, &ifr) < 0) {
+		close(fd);
+		return -1;
+	}
+
+	strcpy(name, ifr.ifr_name);
+	return fd;
+}
+
+FN_TEST(attach_and_detach)
+{
+	char name[IFNAMSIZ] = "";
+	char other_name[IFNAMSIZ];
+	struct ifreq ifr;
+	unsigned int features;
+	int fd, fd2;
+
+	fd = TEST_SUCC(open("/dev/net/tun", O_RDWR));
+	TEST_ERRNO(ioctl(fd, TUNGETIFF, &ifr), EBADFD);
+	TEST_ERRNO(read(fd, buf, sizeof(buf)), EBADFD);
+	TEST_RES(ioctl(fd, TUNGETFEATURES, &features),
+		 (features & (IFF_TUN | IFF_TAP | IFF_NO_PI | IFF_VNET_HDR)) ==
+			 (IFF_TUN | IFF_TAP | IFF_NO_PI | IFF_VNET_HDR));
+
+	// Exactly one of `IFF_TUN` and `IFF_TAP` must be specified.
+	memset(&ifr, 0, sizeof(ifr));
+	ifr.ifr_flags = IFF_TUN | IFF_TAP;
+	TEST_ERRNO(ioctl(fd, TUNSETIFF, &ifr), EINVAL);
+	TEST_SUCC(close(fd));
+
+	// The name is generated if it is not specified.
+	fd = TEST_RES(open_tun(name, IFF_TUN | IFF_NO_PI),
+		      strncmp(name, "tun", 3) == 0);
+	TEST_RES(ioctl(fd, TUNGETIFF, &ifr),
+		 strcmp(ifr.ifr_name, name) == 0 &&
+			 (ifr.ifr_flags & (IFF_TUN | IFF_TAP | IFF_NO_PI)) ==
+				 (IFF_TUN | IFF_NO_PI));
+	TEST_RES(get_link(name, 0), _ret > 0);
+
+	// The file is already attached.
+	memset(&ifr, 0, sizeof(ifr));
+	ifr.ifr_flags = IFF_TUN;
+	TEST_ERRNO(ioctl(fd, TUNSETIFF, &ifr), EINVAL);
+
+	// The device is already attached, or has a different kind.
+	strcpy(other_name, name);
+	TEST_ERRNO(open_tun(other_name, IFF_TUN), EBUSY);
+	TEST_ERRNO(open_tun(other_name, IFF_TAP), EINVAL);
+
+	// The iface is not a TUN/TAP device.
+	strcpy(other_name, "lo");
+	TEST_ERRNO(open_tun(other_name, IFF_TUN), EINVAL);
+
+	// The packet is not an IP packet.
+	TEST_ERRNO(write(fd, "x", 1), EINVAL);
+
+	// The device is removed when the file is closed, unless it is
+	// persistent.
+	TEST_SUCC(ioctl(fd, TUNSETPERSIST, 1));
+	TEST_SUCC(close(fd));
+	TEST_RES(get_link(name, 0), _ret > 0);
+
+	fd = TEST_SUCC(open_tun(name, IFF_TUN));
+	TEST_SUCC(ioctl(fd, TUNSETPERSIST, 0));
+	fd2 = TEST_SUCC(dup(fd));
+	TEST_SUCC(close(fd));
+	TEST_RES(get_link(name, 0), _ret > 0);
+	TEST_SUCC(close(fd2));
+	TEST_ERRNO(get_link(name, 0), ENODEV);
+}
+END_TEST()
+
+static unsigned short checksum(const void *data, size_t len)
+{
+	const unsigned char *bytes = data;
+	unsigned int sum = 0;
+	size_t i;
+
+	for (i = 0; i + 1 < len; i += 2)
+		sum += (bytes[i] << 8) | bytes[i + 1];
+	if (len % 2 == 1)
+		sum += bytes[len - 1] << 8;
+	while (sum >> 16)
+		sum = (sum & 0xffff) + (sum >> 16);
+
+	return htons(~sum);
+}
+
+static int add_addr(int index, const char *addr, int prefix_len)
+{
+	struct nlmsghdr *nlh = (struct nlmsghdr *)buf;
+	struct ifaddrmsg ifa = {
+		.ifa_family = AF_INET,
+		.ifa_prefixlen = prefix_len,
+		.ifa_index = index,
+	};
+	struct in_addr in;
+
+	memset(buf, 0, sizeof(buf));
+	nlh->nlmsg_len = NLMSG_LENGTH(sizeof(ifa));
+	nlh->nlmsg_type = RTM_NEWADDR;
+	nlh->nlmsg_flags = NLM_F_REQUEST | NLM_F_CREATE | NLM_F_EXCL;
+	nlh->nlmsg_seq = ++seq;
+	memcpy(NLMSG_DATA(nlh), &ifa, sizeof(ifa));
+
+	inet_pton(AF_INET, addr, &in);
+	add_attr(nlh, IFA_LOCAL, &in, sizeof(in));
+	add_attr(nlh, IFA_ADDRESS, &in, sizeof(in));
+
+	return send_request(nlh);
+}
+
+/*
+ * Reads a packet from the device, skipping the packets for which `match`
+ * returns false. Returns the length of the packet, or -1 if no packet matches.
+ */
+static ssize_t read_packet(int fd, char *packet, size_t len,
+			   int (*match)(const char *, size_t))
+{
+	struct pollfd pfd = { .fd = fd, .events = POLLIN };
+	ssize_t ret;
+
+	for (;;) {
+		if (poll(&pfd, 1, 100) != 1) {
+			errno = ETIMEDOUT;
+			return -1;
+		}
+
+		ret = read(fd, packet, len);
+		if (ret < 0)
+			return -1;
+		if (match(packet, ret))
+			return ret;
+	}
+}
+
+struct pkt_icmp {
+	struct tun_pi pi;
+	struct iphdr ip;
+	struct icmphdr icmp;
+} __attribute__((packed));
+
+static int is_echo_reply(const char *packet, size_t len)
+{
+	const struct pkt_icmp *pkt = (const struct pkt_icmp *)packet;
+
+	return len == sizeof(*pkt) && pkt->pi.proto == htons(ETH_P_IP) &&
+	       pkt->ip.protocol == IPPROTO_ICMP &&
+	       pkt->icmp.type == ICMP_ECHOREPLY &&
+	       pkt->icmp.un.echo.id == htons(0x1234) &&
+	       pkt->icmp.un.echo.sequence == htons(1);
+}
+
+struct pkt_udp {
+	struct tun_pi pi;
+	struct iphdr ip;
+	struct udphdr udp;
+	char data[sizeof(TEST_DATA)];
+} __attribute__((packed));
+
+static int is_udp_packet(const char *packet, size_t len)
+{
+	const struct pkt_udp *pkt = (const struct pkt_udp *)packet;
+
+	return len == sizeof(*pkt) && pkt->pi.proto == htons(ETH_P_IP) &&
+	       pkt->ip.protocol == IPPROTO_UDP &&
+	       pkt->udp.dest == htons(TEST_PORT) &&
+	       memcmp(pkt->data, TEST_DATA, sizeof(TEST_DATA)) == 0;
+}
+
+FN_TEST(tun_packets)
+{
+	char name[IFNAMSIZ] = "tuntest0";
+	struct pkt_icmp ping;
+	struct pkt_udp udp;
+	struct sockaddr_in addr;
+	int fd, sk, index;
+
+	fd = TEST_SUCC(open_tun(name, IFF_TUN));
+	index = TEST_RES(get_link(name, 0), _ret > 0);
+	TEST_SUCC(add_addr(index, "10.9.0.1", 24));
+
+	// The iface replies to a ping from the peer.
+	memset(&ping, 0, sizeof(ping));
+	ping.pi.proto = htons(ETH_P_IP);
+	ping.ip.version = 4;
+	ping.ip.ihl = 5;
+	ping.ip.tot_len = htons(sizeof(ping) - sizeof(ping.pi));
+	ping.ip.ttl = 64;
+	ping.ip.protocol = IPPROTO_ICMP;
+	inet_pton(AF_INET, "10.9.0.2", &ping.ip.saddr);
+	inet_pton(AF_INET, "10.9.0.1", &ping.ip.daddr);
+	ping.ip.check = checksum(&ping.ip, sizeof(ping.ip));
+	ping.icmp.type = ICMP_ECHO;
+	ping.icmp.un.echo.id = htons(0x1234);
+	ping.icmp.un.echo.sequence = htons(1);
+	ping.icmp.checksum = checksum(&ping.icmp, sizeof(ping.icmp));
+
+	TEST_RES(write(fd, &ping, sizeof(ping)), _ret == sizeof(ping));
+	TEST_RES(read_packet(fd, buf, sizeof(buf), is_echo_reply),
+		 _ret == sizeof(ping));
+
+	// The packets sent to the peer can be read from the device.
+	sk = TEST_SUCC(socket(AF_INET, SOCK_DGRAM, 0));
+	addr.sin_family = AF_INET;
+	addr.sin_port = htons(TEST_PORT);
+	inet_pton(AF_INET, "10.9.0.2", &addr.sin_addr);
+	TEST_RES(sendto(sk, TEST_DATA, sizeof(TEST_DATA), 0,
+			(struct sockaddr *)&addr, sizeof(addr)),
+		 _ret == sizeof(TEST_DATA));
+	TEST_RES(read_packet(fd, (char *)&udp, sizeof(udp), is_udp_packet),
+		 _ret == sizeof(udp));
+
+	// The packet is truncated if the buffer is too small.
+	TEST_RES(sendto(sk, TEST_DATA, sizeof(TEST_DATA), 0,
+			(struct sockaddr *)&addr, sizeof(addr)),
+		 _ret == sizeof(TEST_DATA));
+	TEST_RES(poll(&(struct pollfd){ .fd = fd, .events = POLLIN }, 1, 100),
+		 _ret == 1);
+	TEST_RES(read(fd, &udp, sizeof(udp.pi) + 1),
+		 _ret == sizeof(udp.pi) + 1 && udp.pi.flags == TUN_PKT_STRIP);
+
+	TEST_SUCC(close(sk));
+	TEST_SUCC(close(fd));
+}
+END_TEST()
+
+FN_TEST(tap_vnet_hdr)
+{
+	char name[IFNAMSIZ] = "taptest0";
+	struct virtio_net_hdr hdr;
+	char frame[sizeof(hdr) + ETH_HLEN];
+	int fd, size;
+
+	fd = TEST_SUCC(open_tun(name, IFF_TAP | IFF_NO_PI | IFF_VNET_HDR));
+
+	TEST_RES(ioctl(fd, TUNGETVNETHDRSZ, &size), size == sizeof(hdr));
+	size = sizeof(hdr) - 1;
+	TEST_ERRNO(ioctl(fd, TUNSETVNETHDRSZ, &size), EINVAL);
+	size = sizeof(hdr) + 2;
+	TEST_SUCC(ioctl(fd, TUNSETVNETHDRSZ, &size));
+	TEST_RES(ioctl(fd, TUNGETVNETHDRSZ, &size), size == sizeof(hdr) + 2);
+	size = sizeof(hdr);
+	TEST_SUCC(ioctl(fd, TUNSETVNETHDRSZ, &size));
+
+	TEST_SUCC(ioctl(fd, TUNSETOFFLOAD, TUN_F_CSUM));
+	TEST_ERRNO(ioctl(fd, TUNSETOFFLOAD, 0x10000), EINVAL);
+
+	// Segmentation offload is not supported.
+	memset(frame, 0, sizeof(frame));
+	hdr = (struct virtio_net_hdr){ .gso_type = VIRTIO_NET_HDR_GSO_TCPV4 };
+	memcpy(frame, &hdr, sizeof(hdr));
+	TEST_ERRNO(write(fd, frame, sizeof(frame)), EINVAL);
+
+	// The checksum offset is out of bounds.
+	hdr = (struct virtio_net_hdr){
+		.flags = VIRTIO_NET_HDR_F_NEEDS_CSUM,
+		.csum_start = ETH_HLEN,
+		.csum_offset = 6,
+	};
+	memcpy(frame, &hdr, sizeof(hdr));
+	TEST_ERRNO(write(fd, frame, sizeof(frame)), EINVAL);
+
+	// The frame is too short.
+	memset(frame, 0, sizeof(frame));
+	TEST_ERRNO(write(fd, frame, sizeof(frame) - 1), EINVAL);
+	TEST_RES(write(fd, frame, sizeof(frame)), _ret == sizeof(frame));
+
+	TEST_SUCC(close(fd));
+}
+END_TEST()
+
+struct test_frame {
+	struct ether_header eth;
+	char data[sizeof(TEST_DATA)];
+} __attribute__((packed));
+
+static int is_test_frame(const char *frame, size_t len)
+{
+	const struct test_frame *test = (const struct test_frame *)frame;
+
+	return len == sizeof(*test) &&
+	       test->eth.ether_type == htons(TEST_ETHERTYPE) &&
+	       memcmp(test->data, TEST_DATA, sizeof(TEST_DATA)) == 0;
+}
+
+/*
+ * Sends a test frame from `src` to `dst` through the TAP device `fd`.
+ */
+static int send_frame(int fd, unsigned char src, unsigned char dst)
+{
+	struct test_frame frame = {
+		.eth = {
+			.ether_dhost = { 0x02, 0, 0, 0, 0, dst },
+			.ether_shost = { 0x02, 0, 0, 0, 0, src },
+			.ether_type = htons(TEST_ETHERTYPE),
+		},
+		.data = TEST_DATA,
+	};
+
+	return write(fd, &frame, sizeof(frame));
+}
+
+/*
+ * Returns one if a test frame can be read from the TAP device `fd`, or zero if
+ * no test frame arrives.
+ */
+static int recv_frame(int fd)
+{
+	struct test_frame frame;
+
+	if (read_packet(fd, (char *)&frame, sizeof(frame), is_test_frame) >= 0)
+		return 1;
+
+	return errno == ETIMEDOUT ? 0 : -1;
+}
+
+FN_TEST(bridge)
+{
+	char names[3][IFNAMSIZ] = { "tapbr0", "tapbr1", "tapbr2" };
+	struct nlmsghdr *nlh;
+	int fds[3];
+	int i, br_index, lo_index;
+
+	nlh = init_link_request(RTM_NEWLINK, NLM_F_CREATE | NLM_F_EXCL, 0);
+	add_attr_str(nlh, IFLA_IFNAME, "brtest0");
+	add_link_kind(nlh, "bridge");
+	TEST_SUCC(send_request(nlh));
+	br_index = TEST_RES(get_link("brtest0", 0), _ret > 0);
+	lo_index = TEST_RES(get_link("lo", 0), _ret > 0);
+
+	nlh = init_link_request(RTM_NEWLINK, NLM_F_CREATE | NLM_F_EXCL, 0);
+	add_attr_str(nlh, IFLA_IFNAME, "brtest0");
+	add_link_kind(nlh, "bridge");
+	TEST_ERRNO(send_request(nlh), EEXIST);
+
+	// The link does not exist and is not created.
+	nlh = init_link_request(RTM_NEWLINK, 0, 0);
+	add_attr_str(nlh, IFLA_IFNAME, "brtest1");
+	add_link_kind(nlh, "bridge");
+	TEST_ERRNO(send_request(nlh), ENODEV);
+
+	nlh = init_link_request(RTM_NEWLINK, NLM_F_CREATE | NLM_F_EXCL, 0);
+	add_attr_str(nlh, IFLA_IFNAME, "brtest1");
+	add_link_kind(nlh, "veth");
+	TEST_ERRNO(send_request(nlh), EOPNOTSUPP);
+
+	for (i = 0; i < 3; ++i) {
+		fds[i] = TEST_SUCC(open_tun(names[i], IFF_TAP | IFF_NO_PI));
+		TEST_SUCC(set_master(names[i], br_index));
+		TEST_RES(get_link(names[i], IFLA_MASTER), _ret == br_index);
+	}
+
+	// Only TAP devices can be ports, and only bridges can be masters.
+	TEST_ERRNO(set_master("lo", br_index), EOPNOTSUPP);
+	TEST_ERRNO(set_master(names[0], lo_index), EOPNOTSUPP);
+
+	// The destination is unknown, so the frame is flooded.
+	TEST_RES(send_frame(fds[0], 0xa, 0xb), _ret == sizeof(struct test_frame));
+	TEST_RES(recv_frame(fds[0]), _ret == 0);
+	TEST_RES(recv_frame(fds[1]), _ret == 1);
+	TEST_RES(recv_frame(fds[2]), _ret == 1);
+
+	// The destination has been learned from the previous frame.
+	TEST_RES(send_frame(fds[1], 0xb, 0xa), _ret == sizeof(struct test_frame));
+	TEST_RES(recv_frame(fds[0]), _ret == 1);
+	TEST_RES(recv_frame(fds[2]), _ret == 0);
+
+	TEST_RES(send_frame(fds[0], 0xa, 0xb), _ret == sizeof(struct test_frame));
+	TEST_RES(recv_frame(fds[1]), _ret == 1);
+	TEST_RES(recv_frame(fds[2]), _ret == 0);
+
+	// The released port no longer receives flooded frames.
+	TEST_SUCC(set_master(names[2], 0));
+	TEST_RES(get_link(names[2], IFLA_MASTER), _ret == 0);
+	TEST_RES(send_frame(fds[0], 0xa, 0xc), _ret == sizeof(struct test_frame));
+	TEST_RES(recv_frame(fds[1]), _ret == 1);
+	TEST_RES(recv_frame(fds[2]), _ret == 0);
+
+	// The ports are released when the bridge is deleted.
+	TEST_ERRNO(del_link("lo"), EOPNOTSUPP);
+	TEST_SUCC(del_link("brtest0"));
+	TEST_ERRNO(get_link("brtest0", 0), ENODEV);
+	TEST_RES(get_link(names[0], IFLA_MASTER), _ret == 0);
+
+	for (i = 0; i < 3; ++i)
+		TEST_SUCC(close(fds[i]));
+}
+END_TEST()
@@ -0,0 +1,329 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <fcntl.h>
+#include <stddef.h>
+#include <sys/socket.h>
+#include <sys/un.h>
+#include <unistd.h>
+
+#include "test.h"
+
+static int sk_stream[2];
+static int sk_dgram[2];
+static int pipe_fds[2];
+
+FN_SETUP(socketpair)
+{
+	CHECK(socketpair(PF_UNIX, SOCK_STREAM, 0, sk_stream));
+	CHECK(socketpair(PF_UNIX, SOCK_DGRAM, 0, sk_dgram));
+	CHECK(pipe(pipe_fds));
+}
+END_SETUP()
+
+static int send_fd(int sk, int fd, char data)
+{
+	char cbuf[CMSG_SPACE(sizeof(int))] = { 0 };
+	struct iovec iov = { .iov_base = &data, .iov_len = 1 };
+	struct msghdr msg = {
+		.msg_iov = &iov,
+		.msg_iovlen = 1,
+		.msg_control = cbuf,
+		.msg_controllen = sizeof(cbuf),
+	};
+	struct cmsghdr *cmsg = CMSG_FIRSTHDR(&msg);
+
+	cmsg->cmsg_level = SOL_SOCKET;
+	cmsg->cmsg_type = SCM_RIGHTS;
+	cmsg->cmsg_len = CMSG_LEN(sizeof(int));
+	memcpy(CMSG_DATA(cmsg), &fd, sizeof(int));
+
+	return sendmsg(sk, &msg, 0);
+}
+
+static char recv_cbuf[CMSG_SPACE(sizeof(struct ucred)) +
+		      CMSG_SPACE(sizeof(int))];
+static struct msghdr recv_msg;
+
+static int recv_with_cmsg(int sk, char *buf, size_t len, size_t controllen,
+			  int flags)
+{
+	static struct iovec iov;
+
+	iov.iov_base = buf;
+	iov.iov_len = len;
+
+	memset(recv_cbuf, 0, sizeof(recv_cbuf));
+	memset(&recv_msg, 0, sizeof(recv_msg));
+	recv_msg.msg_iov = &iov;
+	recv_msg.msg_iovlen = 1;
+	recv_msg.msg_control = recv_cbuf;
+	recv_msg.msg_controllen = controllen;
+
+	return recvmsg(sk, &recv_msg, flags);
+}
+
+static int received_fd(void)
+{
+	struct cmsghdr *cmsg;
+	int fd;
+
+	for (cmsg = CMSG_FIRSTHDR(&recv_msg); cmsg != NULL;
+	     cmsg = CMSG_NXTHDR(&recv_msg, cmsg)) {
+		if (cmsg->cmsg_level == SOL_SOCKET &&
+		    cmsg->cmsg_type == SCM_RIGHTS &&
+		    cmsg->cmsg_len == CMSG_LEN(sizeof(int))) {
+			memcpy(&fd, CMSG_DATA(cmsg), sizeof(int));
+			return fd;
+		}
+	}
+
+	return -1;
+}
+
+static struct ucred *received_cred(void)
+{
+	struct cmsghdr *cmsg;
+
+	for (cmsg = CMSG_FIRSTHDR(&recv_msg); cmsg != NULL;
+	     cmsg = CMSG_NXTHDR(&recv_msg, cmsg)) {
+		if (cmsg->cmsg_level == SOL_SOCKET &&
+		    cmsg->cmsg_type == SCM_CREDENTIALS &&
+		    cmsg->cmsg_len == CMSG_LEN(sizeof(struct ucred)))
+			return (struct ucred *)CMSG_DATA(cmsg);
+	}
+
+	return NULL;
+}
+
+FN_TEST(scm_rights_stream)
+{
+	char buf[2];
+	int fd;
+
+	TEST_RES(send_fd(sk_stream[0], pipe_fds[1], 'a'), _ret == 1);
+	TEST_RES(recv_with_cmsg(sk_stream[1], buf, 1, sizeof(recv_cbuf), 0),
+		 _ret == 1 && buf[0] == 'a' && !(recv_msg.msg_flags & MSG_CTRUNC));
+
+	fd = TEST_RES(received_fd(), _ret >= 0 && _ret != pipe_fds[1]);
+	TEST_RES(fcntl(fd, F_GETFD), !(_ret & FD_CLOEXEC));
+	TEST_RES(write(fd, "b", 1), _ret == 1);
+	TEST_RES(read(pipe_fds[0], buf, 1), _ret == 1 && buf[0] == 'b');
+	TEST_SUCC(close(fd));
+}
+END_TEST()
+
+FN_TEST(scm_rights_cloexec)
+{
+	char buf[1];
+	int fd;
+
+	TEST_RES(send_fd(sk_stream[0], pipe_fds[1], 'a'), _ret == 1);
+	TEST_RES(recv_with_cmsg(sk_stream[1], buf, 1, sizeof(recv_cbuf),
+				MSG_CMSG_CLOEXEC),
+		 _ret == 1 && buf[0] == 'a');
+
+	fd = TEST_RES(received_fd(), _ret >= 0);
+	TEST_RES(fcntl(fd, F_GETFD), _ret & FD_CLOEXEC);
+	TEST_SUCC(close(fd));
+}
+END_TEST()
+
+FN_TEST(scm_rights_truncated)
+{
+	char buf[1];
+
+	TEST_RES(send_fd(sk_stream[0], pipe_fds[1], 'a'), _ret == 1);
+	TEST_RES(recv_with_cmsg(sk_stream[1], buf, 1, 0, 0),
+		 _ret == 1 && buf[0] == 'a' && (recv_msg.msg_flags & MSG_CTRUNC));
+
+	TEST_RES(send_fd(sk_stream[0], pipe_fds[1], 'a'), _ret == 1);
+	TEST_RES(recv_with_cmsg(sk_stream[1], buf, 1, sizeof(struct cmsghdr),
+				0),
+		 _ret == 1 && buf[0] == 'a' && (recv_msg.msg_flags & MSG_CTRUNC));
+}
+END_TEST()
+
+FN_TEST(scm_rights_boundary)
+{
+	char buf[4];
+	int fd;
+
+	// A read cannot pass the end of the data with files attached.
+	TEST_RES(send_fd(sk_stream[0], pipe_fds[1], 'a'), _ret == 1);
+	TEST_RES(write(sk_stream[0], "y", 1), _ret == 1);
+
+	TEST_RES(recv_with_cmsg(sk_stream[1], buf, sizeof(buf),
+				sizeof(recv_cbuf), 0),
+		 _ret == 1 && buf[0] == 'a');
+	fd = TEST_RES(received_fd(), _ret >= 0);
+	TEST_SUCC(close(fd));
+
+	TEST_RES(recv_with_cmsg(sk_stream[1], buf, sizeof(buf),
+				sizeof(recv_cbuf), 0),
+		 _ret == 1 && buf[0] == 'y' && received_fd() < 0);
+}
+END_TEST()
+
+FN_TEST(scm_rights_dgram)
+{
+	char buf[1];
+	int fd;
+
+	TEST_RES(send_fd(sk_dgram[0], pipe_fds[1], 'a'), _ret == 1);
+	TEST_RES(recv_with_cmsg(sk_dgram[1], buf, 1, sizeof(recv_cbuf), 0),
+		 _ret == 1 && buf[0] == 'a');
+
+	fd = TEST_RES(received_fd(), _ret >= 0);
+	TEST_RES(write(fd, "b", 1), _ret == 1);
+	TEST_RES(read(pipe_fds[0], buf, 1), _ret == 1 && buf[0] == 'b');
+	TEST_SUCC(close(fd));
+}
+END_TEST()
+
+FN_TEST(scm_rights_badfd)
+{
+	TEST_ERRNO(send_fd(sk_stream[0], 1000, 'a'), EBADF);
+	TEST_ERRNO(send_fd(sk_dgram[0], 1000, 'a'), EBADF);
+}
+END_TEST()
+
+FN_TEST(scm_rights_cycle)
+{
+	int sk[2];
+
+	// The sockets are only reachable from their own receive queues after
+	// being closed. They should be collected without any leaks or hangs.
+	TEST_SUCC(socketpair(PF_UNIX, SOCK_STREAM, 0, sk));
+	TEST_RES(send_fd(sk[0], sk[0], 'a'), _ret == 1);
+	TEST_RES(send_fd(sk[0], sk[1], 'a'), _ret == 1);
+	TEST_SUCC(close(sk[0]));
+	TEST_SUCC(close(sk[1]));
+
+	TEST_SUCC(socketpair(PF_UNIX, SOCK_DGRAM, 0, sk));
+	TEST_RES(send_fd(sk[0], sk[1], 'a'), _ret == 1);
+	TEST_RES(send_fd(sk[1], sk[0], 'a'), _ret == 1);
+	TEST_SUCC(close(sk[0]));
+	TEST_SUCC(close(sk[1]));
+}
+END_TEST()
+
+FN_TEST(peer_cred)
+{
+	struct ucred cred;
+	socklen_t len = sizeof(cred);
+
+	TEST_RES(getsockopt(sk_stream[0], SOL_SOCKET, SO_PEERCRED, &cred, &len),
+		 len == sizeof(cred) && cred.pid == getpid() &&
+			 cred.uid == geteuid() && cred.gid == getegid());
+	TEST_RES(getsockopt(sk_dgram[1], SOL_SOCKET, SO_PEERCRED, &cred, &len),
+		 len == sizeof(cred) && cred.pid == getpid() &&
+			 cred.uid == geteuid() && cred.gid == getegid());
+}
+END_TEST()
+
+FN_TEST(pass_cred)
+{
+	int one = 1, zero = 0, val;
+	socklen_t len = sizeof(val);
+	struct ucred *cred;
+	char buf[1];
+
+	TEST_RES(getsockopt(sk_stream[1], SOL_SOCKET, SO_PASSCRED, &val, &len),
+		 len == sizeof(val) && val == 0);
+	TEST_SUCC(setsockopt(sk_stream[1], SOL_SOCKET, SO_PASSCRED, &one,
+			     sizeof(one)));
+	TEST_RES(getsockopt(sk_stream[1], SOL_SOCKET, SO_PASSCRED, &val, &len),
+		 len == sizeof(val) && val == 1);
+
+	TEST_RES(write(sk_stream[0], "a", 1), _ret == 1);
+	TEST_RES(recv_with_cmsg(sk_stream[1], buf, 1, sizeof(recv_cbuf), 0),
+		 _ret == 1 && buf[0] == 'a');
+	cred = received_cred();
+	TEST_RES(cred ? cred->pid : 0, _ret == getpid());
+
+	TEST_SUCC(setsockopt(sk_stream[1], SOL_SOCKET, SO_PASSCRED, &zero,
+			     sizeof(zero)));
+
+	TEST_SUCC(setsockopt(sk_dgram[1], SOL_SOCKET, SO_PASSCRED, &one,
+			     sizeof(one)));
+	TEST_RES(write(sk_dgram[0], "a", 1), _ret == 1);
+	TEST_RES(recv_with_cmsg(sk_dgram[1], buf, 1, sizeof(recv_cbuf), 0),
+		 _ret == 1 && buf[0] == 'a');
+	cred = received_cred();
+	TEST_RES(cred ? cred->uid : -1, _ret == geteuid());
+	TEST_SUCC(setsockopt(sk_dgram[1], SOL_SOCKET, SO_PASSCRED, &zero,
+			     sizeof(zero)));
+}
+END_TEST()
+
+#define ABSTRACT_ADDR(name) \
+	((struct sockaddr_un){ .sun_family = AF_UNIX, .sun_path = "\0" name })
+
+#define ABSTRACT_ADDRLEN(name) (offsetof(struct sockaddr_un, sun_path) + sizeof(name))
+
+FN_TEST(dgram_bound)
+{
+	struct sockaddr_un addr1 = ABSTRACT_ADDR("unix_scm_D1");
+	struct sockaddr_un addr2 = ABSTRACT_ADDR("unix_scm_D2");
+	socklen_t addrlen1 = ABSTRACT_ADDRLEN("unix_scm_D1");
+	socklen_t addrlen2 = ABSTRACT_ADDRLEN("unix_scm_D2");
+	struct sockaddr_un from;
+	socklen_t fromlen;
+	char buf[8];
+	int sk1, sk2;
+
+	sk1 = TEST_SUCC(socket(PF_UNIX, SOCK_DGRAM, 0));
+	sk2 = TEST_SUCC(socket(PF_UNIX, SOCK_DGRAM, 0));
+	TEST_SUCC(bind(sk1, (struct sockaddr *)&addr1, addrlen1));
+	TEST_SUCC(bind(sk2, (struct sockaddr *)&addr2, addrlen2));
+
+	TEST_ERRNO(send(sk1, "hello", 5, 0), ENOTCONN);
+	TEST_RES(sendto(sk1, "hello", 5, 0, (struct sockaddr *)&addr2,
+			addrlen2),
+		 _ret == 5);
+	TEST_RES(sendto(sk1, "world!", 6, 0, (struct sockaddr *)&addr2,
+			addrlen2),
+		 _ret == 6);
+
+	// Message boundaries are preserved.
+	fromlen = sizeof(from);
+	TEST_RES(recvfrom(sk2, buf, sizeof(buf), 0, (struct sockaddr *)&from,
+			  &fromlen),
+		 _ret == 5 && memcmp(buf, "hello", 5) == 0 &&
+			 fromlen == addrlen1 &&
+			 memcmp(&from, &addr1, addrlen1) == 0);
+	TEST_RES(recv(sk2, buf, 2, 0), _ret == 2 && memcmp(buf, "wo", 2) == 0);
+	TEST_ERRNO(recv(sk2, buf, sizeof(buf), MSG_DONTWAIT), EAGAIN);
+
+	// A socket connected to another socket rejects messages from others.
+	TEST_SUCC(connect(sk2, (struct sockaddr *)&addr1, addrlen1));
+	TEST_SUCC(connect(sk1, (struct sockaddr *)&addr2, addrlen2));
+	TEST_RES(send(sk2, "a", 1, 0), _ret == 1);
+	TEST_RES(recv(sk1, buf, sizeof(buf), 0), _ret == 1 && buf[0] == 'a');
+	TEST_ERRNO(sendto(sk_dgram[0], "a", 1, 0, (struct sockaddr *)&addr2,
+			  addrlen2),
+		   EPERM);
+
+	TEST_SUCC(close(sk1));
+	TEST_SUCC(close(sk2));
+}
+END_TEST()
+
+FN_TEST(dgram_shutdown)
+{
+	char buf[1];
+
+	// Unlike stream sockets, shutting down a datagram socket does not
+	// affect its peer.
+	TEST_SUCC(shutdown(sk_dgram[0], SHUT_WR));
+	TEST_ERRNO(send(sk_dgram[0], "a", 1, MSG_NOSIGNAL), EPIPE);
+	TEST_RES(send(sk_dgram[1], "a", 1, 0), _ret == 1);
+	TEST_RES(recv(sk_dgram[0], buf, 1, 0), _ret == 1 && buf[0] == 'a');
+
+	TEST_SUCC(shutdown(sk_dgram[0], SHUT_RD));
+	TEST_ERRNO(send(sk_dgram[1], "a", 1, MSG_NOSIGNAL), EPIPE);
+	TEST_RES(recv(sk_dgram[0], buf, 1, 0), _ret == 0);
+}
+END_TEST()
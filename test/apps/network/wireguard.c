@@ -0,0 +1,468 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <arpa/inet.h>
+#include <linux/genetlink.h>
+#include <linux/netlink.h>
+#include <linux/rtnetlink.h>
+#include <linux/wireguard.h>
+#include <net/if.h>
+#include <netinet/in.h>
+#include <poll.h>
+#include <sys/socket.h>
+#include <unistd.h>
+
+#include "test.h"
+
+#define BUF_SIZE 8192
+#define WG_PORT0 0x3a2b
+#define WG_PORT1 0x3a2c
+#define TEST_PORT 0x1a2b
+#define TEST_DATA "hello"
+
+// The key pairs are from RFC 7748, Section 6.1.
+static const unsigned char private_key0[WG_KEY_LEN] = {
+	0x77, 0x07, 0x6d, 0x0a, 0x73, 0x18, 0xa5, 0x7d, 0x3c, 0x16, 0xc1,
+	0x72, 0x51, 0xb2, 0x66, 0x45, 0xdf, 0x4c, 0x2f, 0x87, 0xeb, 0xc0,
+	0x99, 0x2a, 0xb1, 0x77, 0xfb, 0xa5, 0x1d, 0xb9, 0x2c, 0x2a,
+};
+static const unsigned char public_key0[WG_KEY_LEN] = {
+	0x85, 0x20, 0xf0, 0x09, 0x89, 0x30, 0xa7, 0x54, 0x74, 0x8b, 0x7d,
+	0xdc, 0xb4, 0x3e, 0xf7, 0x5a, 0x0d, 0xbf, 0x3a, 0x0d, 0x26, 0x38,
+	0x1a, 0xf4, 0xeb, 0xa4, 0xa9, 0x8e, 0xaa, 0x9b, 0x4e, 0x6a,
+};
+static const unsigned char private_key1[WG_KEY_LEN] = {
+	0x5d, 0xab, 0x08, 0x7e, 0x62, 0x4a, 0x8a, 0x4b, 0x79, 0xe1, 0x7f,
+	0x8b, 0x83, 0x80, 0x0e, 0xe6, 0x6f, 0x3b, 0xb1, 0x29, 0x26, 0x18,
+	0xb6, 0xfd, 0x1c, 0x2f, 0x8b, 0x27, 0xff, 0x88, 0xe0, 0xeb,
+};
+static const unsigned char public_key1[WG_KEY_LEN] = {
+	0xde, 0x9e, 0xdb, 0x7d, 0x7b, 0x7d, 0xc1, 0xb4, 0xd3, 0x5b, 0x61,
+	0xc2, 0xec, 0xe4, 0x35, 0x37, 0x3f, 0x83, 0x43, 0xc8, 0x5b, 0x78,
+	0x67, 0x4d, 0xad, 0xfc, 0x7e, 0x14, 0x6f, 0x88, 0x2b, 0x4f,
+};
+
+static int sk_route;
+static int sk_genl;
+static int family_id;
+static unsigned int seq;
+static char buf[BUF_SIZE];
+
+FN_SETUP(socket)
+{
+	sk_route = CHECK(socket(AF_NETLINK, SOCK_RAW, NETLINK_ROUTE));
+	sk_genl = CHECK(socket(AF_NETLINK, SOCK_RAW, NETLINK_GENERIC));
+}
+END_SETUP()
+
+static struct nlmsghdr *init_request(int type, int flags, const void *body,
+				     size_t len)
+{
+	struct nlmsghdr *nlh = (struct nlmsghdr *)buf;
+
+	memset(buf, 0, sizeof(buf));
+	nlh->nlmsg_len = NLMSG_LENGTH(len);
+	nlh->nlmsg_type = type;
+	nlh->nlmsg_flags = NLM_F_REQUEST | flags;
+	nlh->nlmsg_seq = ++seq;
+	memcpy(NLMSG_DATA(nlh), body, len);
+
+	return nlh;
+}
+
+static struct nlmsghdr *init_genl_request(int type, int flags, int cmd)
+{
+	struct genlmsghdr genl = { .cmd = cmd, .version = 1 };
+
+	return init_request(type, flags, &genl, sizeof(genl));
+}
+
+static void add_attr(struct nlmsghdr *nlh, int type, const void *data,
+		     size_t len)
+{
+	struct rtattr *rta =
+		(struct rtattr *)((char *)nlh + NLMSG_ALIGN(nlh->nlmsg_len));
+
+	rta->rta_type = type;
+	rta->rta_len = RTA_LENGTH(len);
+	memcpy(RTA_DATA(rta), data, len);
+	nlh->nlmsg_len = NLMSG_ALIGN(nlh->nlmsg_len) + RTA_ALIGN(rta->rta_len);
+}
+
+static void add_attr_str(struct nlmsghdr *nlh, int type, const char *str)
+{
+	add_attr(nlh, type, str, strlen(str) + 1);
+}
+
+static void add_attr_u16(struct nlmsghdr *nlh, int type, unsigned short value)
+{
+	add_attr(nlh, type, &value, sizeof(value));
+}
+
+static void add_attr_u8(struct nlmsghdr *nlh, int type, unsigned char value)
+{
+	add_attr(nlh, type, &value, sizeof(value));
+}
+
+/*
+ * Starts a nested attribute. The attributes added before `end_nested` is called
+ * are put in the nested attribute.
+ */
+static struct rtattr *begin_nested(struct nlmsghdr *nlh, int type)
+{
+	struct rtattr *rta =
+		(struct rtattr *)((char *)nlh + NLMSG_ALIGN(nlh->nlmsg_len));
+
+	rta->rta_type = type | NLA_F_NESTED;
+	nlh->nlmsg_len = NLMSG_ALIGN(nlh->nlmsg_len) + RTA_LENGTH(0);
+
+	return rta;
+}
+
+static void end_nested(struct nlmsghdr *nlh, struct rtattr *rta)
+{
+	rta->rta_len = (char *)nlh + nlh->nlmsg_len - (char *)rta;
+}
+
+/*
+ * Sends the request and waits for the acknowledgment. Returns zero on success
+ * and sets errno to the error code on failure.
+ */
+static int send_request(int sk, struct nlmsghdr *nlh)
+{
+	struct nlmsgerr *err;
+	ssize_t len;
+
+	nlh->nlmsg_flags |= NLM_F_ACK;
+	if (send(sk, nlh, nlh->nlmsg_len, 0) < 0)
+		return -1;
+
+	len = recv(sk, buf, sizeof(buf), 0);
+	if (len < 0)
+		return -1;
+
+	nlh = (struct nlmsghdr *)buf;
+	if (!NLMSG_OK(nlh, len) || nlh->nlmsg_type != NLMSG_ERROR ||
+	    nlh->nlmsg_seq != seq) {
+		errno = EPROTO;
+		return -1;
+	}
+
+	err = NLMSG_DATA(nlh);
+	if (err->error != 0) {
+		errno = -err->error;
+		return -1;
+	}
+
+	return 0;
+}
+
+/*
+ * Sends the request and receives the first reply segment, which is stored in
+ * `buf`. Returns zero on success and sets errno to the error code on failure.
+ */
+static int send_get_request(int sk, struct nlmsghdr *nlh, int type)
+{
+	ssize_t len;
+
+	if (send(sk, nlh, nlh->nlmsg_len, 0) < 0)
+		return -1;
+
+	len = recv(sk, buf, sizeof(buf), 0);
+	if (len < 0)
+		return -1;
+
+	nlh = (struct nlmsghdr *)buf;
+	if (!NLMSG_OK(nlh, len) || nlh->nlmsg_seq != seq) {
+		errno = EPROTO;
+		return -1;
+	}
+	if (nlh->nlmsg_type == NLMSG_ERROR) {
+		errno = -((struct nlmsgerr *)NLMSG_DATA(nlh))->error;
+		return -1;
+	}
+	if (nlh->nlmsg_type != type) {
+		errno = EPROTO;
+		return -1;
+	}
+
+	return 0;
+}
+
+/*
+ * Finds the attribute `type` in the attributes that start at `rta` and span
+ * `len` bytes.
+ */
+static struct rtattr *find_attr(struct rtattr *rta, int len, int type)
+{
+	for (; RTA_OK(rta, len); rta = RTA_NEXT(rta, len))
+		if ((rta->rta_type & NLA_TYPE_MASK) == type)
+			return rta;
+
+	return NULL;
+}
+
+static struct rtattr *find_genl_attr(struct nlmsghdr *seg, int type)
+{
+	struct rtattr *rta =
+		(struct rtattr *)((char *)NLMSG_DATA(seg) + GENL_HDRLEN);
+
+	return find_attr(rta, NLMSG_PAYLOAD(seg, GENL_HDRLEN), type);
+}
+
+/*
+ * Resolves the ID of the generic netlink family named `name`. Sets errno to the
+ * error code on failure.
+ */
+static int resolve_family(const char *name)
+{
+	struct nlmsghdr *nlh;
+	struct rtattr *rta;
+
+	nlh = init_genl_request(GENL_ID_CTRL, 0, CTRL_CMD_GETFAMILY);
+	add_attr_str(nlh, CTRL_ATTR_FAMILY_NAME, name);
+	if (send_get_request(sk_genl, nlh, GENL_ID_CTRL) < 0)
+		return -1;
+
+	rta = find_genl_attr((struct nlmsghdr *)buf, CTRL_ATTR_FAMILY_ID);
+	if (rta == NULL) {
+		errno = EPROTO;
+		return -1;
+	}
+
+	return *(unsigned short *)RTA_DATA(rta);
+}
+
+static int new_link(const char *name, const char *kind)
+{
+	struct ifinfomsg ifi = { .ifi_family = AF_UNSPEC };
+	struct nlmsghdr *nlh;
+	struct rtattr *link_info;
+
+	nlh = init_request(RTM_NEWLINK, NLM_F_CREATE | NLM_F_EXCL, &ifi,
+			   sizeof(ifi));
+	add_attr_str(nlh, IFLA_IFNAME, name);
+	link_info = begin_nested(nlh, IFLA_LINKINFO);
+	add_attr_str(nlh, IFLA_INFO_KIND, kind);
+	end_nested(nlh, link_info);
+
+	return send_request(sk_route, nlh);
+}
+
+static int del_link(const char *name)
+{
+	struct ifinfomsg ifi = { .ifi_family = AF_UNSPEC };
+	struct nlmsghdr *nlh;
+
+	nlh = init_request(RTM_DELLINK, 0, &ifi, sizeof(ifi));
+	add_attr_str(nlh, IFLA_IFNAME, name);
+
+	return send_request(sk_route, nlh);
+}
+
+static int add_addr(const char *name, const char *addr, int prefix_len)
+{
+	struct ifaddrmsg ifa = {
+		.ifa_family = AF_INET,
+		.ifa_prefixlen = prefix_len,
+		.ifa_index = if_nametoindex(name),
+	};
+	struct nlmsghdr *nlh;
+	struct in_addr in;
+
+	nlh = init_request(RTM_NEWADDR, NLM_F_CREATE | NLM_F_EXCL, &ifa,
+			   sizeof(ifa));
+	inet_pton(AF_INET, addr, &in);
+	add_attr(nlh, IFA_LOCAL, &in, sizeof(in));
+	add_attr(nlh, IFA_ADDRESS, &in, sizeof(in));
+
+	return send_request(sk_route, nlh);
+}
+
+/*
+ * Configures the device named `name` with the private key and the listening
+ * port, and adds a peer whose endpoint is `127.0.0.1:peer_port`.
+ */
+static int set_device(const char *name, const unsigned char *private_key,
+		      int port, const unsigned char *peer_key, int peer_port,
+		      const char *allowed_ip)
+{
+	struct sockaddr_in endpoint = {
+		.sin_family = AF_INET,
+		.sin_port = htons(peer_port),
+		.sin_addr = { htonl(INADDR_LOOPBACK) },
+	};
+	struct rtattr *peers, *peer, *allowed_ips, *allowed;
+	struct nlmsghdr *nlh;
+	struct in_addr in;
+
+	nlh = init_genl_request(family_id, 0, WG_CMD_SET_DEVICE);
+	add_attr_str(nlh, WGDEVICE_A_IFNAME, name);
+	add_attr(nlh, WGDEVICE_A_PRIVATE_KEY, private_key, WG_KEY_LEN);
+	add_attr_u16(nlh, WGDEVICE_A_LISTEN_PORT, port);
+
+	peers = begin_nested(nlh, WGDEVICE_A_PEERS);
+	peer = begin_nested(nlh, 0);
+	add_attr(nlh, WGPEER_A_PUBLIC_KEY, peer_key, WG_KEY_LEN);
+	add_attr(nlh, WGPEER_A_ENDPOINT, &endpoint, sizeof(endpoint));
+	allowed_ips = begin_nested(nlh, WGPEER_A_ALLOWEDIPS);
+	allowed = begin_nested(nlh, 0);
+	inet_pton(AF_INET, allowed_ip, &in);
+	add_attr_u16(nlh, WGALLOWEDIP_A_FAMILY, AF_INET);
+	add_attr(nlh, WGALLOWEDIP_A_IPADDR, &in, sizeof(in));
+	add_attr_u8(nlh, WGALLOWEDIP_A_CIDR_MASK, 32);
+	end_nested(nlh, allowed);
+	end_nested(nlh, allowed_ips);
+	end_nested(nlh, peer);
+	end_nested(nlh, peers);
+
+	return send_request(sk_genl, nlh);
+}
+
+/*
+ * Gets the device named `name`. The reply segment is stored in `buf`.
+ */
+static int get_device(const char *name)
+{
+	struct nlmsghdr *nlh;
+
+	nlh = init_genl_request(family_id, NLM_F_DUMP, WG_CMD_GET_DEVICE);
+	add_attr_str(nlh, WGDEVICE_A_IFNAME, name);
+
+	return send_get_request(sk_genl, nlh, family_id);
+}
+
+static int device_has_key(int type, const unsigned char *key)
+{
+	struct rtattr *rta = find_genl_attr((struct nlmsghdr *)buf, type);
+
+	if (key == NULL)
+		return rta == NULL;
+
+	return rta != NULL && RTA_PAYLOAD(rta) == WG_KEY_LEN &&
+	       memcmp(RTA_DATA(rta), key, WG_KEY_LEN) == 0;
+}
+
+static int device_listen_port(void)
+{
+	struct rtattr *rta =
+		find_genl_attr((struct nlmsghdr *)buf, WGDEVICE_A_LISTEN_PORT);
+
+	return rta == NULL ? -1 : *(unsigned short *)RTA_DATA(rta);
+}
+
+/*
+ * Checks whether the public key of the only peer of the device is `key`.
+ */
+static int device_has_peer(const unsigned char *key)
+{
+	struct rtattr *peers, *peer, *rta;
+	int len;
+
+	peers = find_genl_attr((struct nlmsghdr *)buf, WGDEVICE_A_PEERS);
+	if (peers == NULL)
+		return 0;
+
+	peer = RTA_DATA(peers);
+	len = RTA_PAYLOAD(peers);
+	if (!RTA_OK(peer, len) || RTA_OK(RTA_NEXT(peer, len), len))
+		return 0;
+
+	peer = RTA_DATA(peers);
+	rta = find_attr(RTA_DATA(peer), RTA_PAYLOAD(peer), WGPEER_A_PUBLIC_KEY);
+	return rta != NULL && RTA_PAYLOAD(rta) == WG_KEY_LEN &&
+	       memcmp(RTA_DATA(rta), key, WG_KEY_LEN) == 0;
+}
+
+FN_TEST(resolve_family)
+{
+	family_id = TEST_RES(resolve_family(WG_GENL_NAME), _ret > GENL_ID_CTRL);
+	TEST_RES(resolve_family("nlctrl"), _ret == GENL_ID_CTRL);
+	TEST_ERRNO(resolve_family("nonexistent"), ENOENT);
+}
+END_TEST()
+
+FN_TEST(configure)
+{
+	TEST_SUCC(new_link("wgtest0", "wireguard"));
+	TEST_ERRNO(new_link("wgtest0", "wireguard"), EEXIST);
+
+	// The device has no keys before it is configured.
+	TEST_RES(get_device("wgtest0"),
+		 device_has_key(WGDEVICE_A_PRIVATE_KEY, NULL) &&
+			 device_has_key(WGDEVICE_A_PUBLIC_KEY, NULL));
+
+	// The public key is derived from the private key.
+	TEST_SUCC(set_device("wgtest0", private_key0, WG_PORT0, public_key1,
+			     WG_PORT1, "10.8.0.2"));
+	TEST_RES(get_device("wgtest0"),
+		 device_has_key(WGDEVICE_A_PRIVATE_KEY, private_key0) &&
+			 device_has_key(WGDEVICE_A_PUBLIC_KEY, public_key0) &&
+			 device_listen_port() == WG_PORT0 &&
+			 device_has_peer(public_key1));
+
+	// The iface is not a WireGuard iface, or does not exist.
+	TEST_ERRNO(get_device("lo"), EOPNOTSUPP);
+	TEST_ERRNO(get_device("wgtest9"), ENODEV);
+	TEST_ERRNO(set_device("lo", private_key0, WG_PORT0, public_key1,
+			      WG_PORT1, "10.8.0.2"),
+		   EOPNOTSUPP);
+}
+END_TEST()
+
+FN_TEST(tunnel)
+{
+	struct sockaddr_in addr = { .sin_family = AF_INET };
+	struct pollfd pfd = { .events = POLLIN };
+	char data[sizeof(TEST_DATA)];
+	int sk0, sk1;
+
+	TEST_SUCC(new_link("wgtest1", "wireguard"));
+	TEST_SUCC(set_device("wgtest1", private_key1, WG_PORT1, public_key0,
+			     WG_PORT0, "10.8.0.1"));
+	TEST_SUCC(add_addr("wgtest0", "10.8.0.1", 24));
+	TEST_SUCC(add_addr("wgtest1", "10.8.0.2", 24));
+
+	sk0 = TEST_SUCC(socket(AF_INET, SOCK_DGRAM, 0));
+	sk1 = TEST_SUCC(socket(AF_INET, SOCK_DGRAM, 0));
+
+	addr.sin_port = htons(TEST_PORT);
+	inet_pton(AF_INET, "10.8.0.1", &addr.sin_addr);
+	TEST_SUCC(bind(sk0, (struct sockaddr *)&addr, sizeof(addr)));
+	inet_pton(AF_INET, "10.8.0.2", &addr.sin_addr);
+	TEST_SUCC(bind(sk1, (struct sockaddr *)&addr, sizeof(addr)));
+
+	// The first packet is staged until the handshake completes.
+	TEST_RES(sendto(sk0, TEST_DATA, sizeof(TEST_DATA), 0,
+			(struct sockaddr *)&addr, sizeof(addr)),
+		 _ret == sizeof(TEST_DATA));
+	pfd.fd = sk1;
+	TEST_RES(poll(&pfd, 1, 1000), _ret == 1);
+	TEST_RES(recv(sk1, data, sizeof(data), 0),
+		 _ret == sizeof(TEST_DATA) &&
+			 memcmp(data, TEST_DATA, sizeof(TEST_DATA)) == 0);
+
+	// The reply uses the established session.
+	inet_pton(AF_INET, "10.8.0.1", &addr.sin_addr);
+	TEST_RES(sendto(sk1, TEST_DATA, sizeof(TEST_DATA), 0,
+			(struct sockaddr *)&addr, sizeof(addr)),
+		 _ret == sizeof(TEST_DATA));
+	pfd.fd = sk0;
+	TEST_RES(poll(&pfd, 1, 1000), _ret == 1);
+	TEST_RES(recv(sk0, data, sizeof(data), 0),
+		 _ret == sizeof(TEST_DATA) &&
+			 memcmp(data, TEST_DATA, sizeof(TEST_DATA)) == 0);
+
+	TEST_SUCC(close(sk0));
+	TEST_SUCC(close(sk1));
+}
+END_TEST()
+
+FN_TEST(delete)
+{
+	TEST_SUCC(del_link("wgtest0"));
+	TEST_SUCC(del_link("wgtest1"));
+	TEST_ERRNO(get_device("wgtest0"), ENODEV);
+}
+END_TEST()
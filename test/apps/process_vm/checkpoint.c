@@ -0,0 +1,294 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include "../network/test.h"
+#include <elf.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/mman.h>
+#include <sys/prctl.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define KCMP_FILE 0
+#define KCMP_VM 1
+#define KCMP_FILES 2
+
+#define PM_PRESENT (1ULL << 63)
+#define PM_FILE (1ULL << 61)
+#define PM_MMAP_EXCLUSIVE (1ULL << 56)
+
+// The same as `struct prctl_mm_map` in `linux/prctl.h`.
+struct mm_map {
+	uint64_t start_code;
+	uint64_t end_code;
+	uint64_t start_data;
+	uint64_t end_data;
+	uint64_t start_brk;
+	uint64_t brk;
+	uint64_t start_stack;
+	uint64_t arg_start;
+	uint64_t arg_end;
+	uint64_t env_start;
+	uint64_t env_end;
+	uint64_t *auxv;
+	uint32_t auxv_size;
+	uint32_t exe_fd;
+};
+
+static long page_size;
+static pid_t child;
+static int pipefd[2];
+
+static long kcmp(pid_t pid1, pid_t pid2, int type, unsigned long idx1,
+		 unsigned long idx2)
+{
+	return syscall(SYS_kcmp, pid1, pid2, type, idx1, idx2);
+}
+
+FN_SETUP(spawn)
+{
+	char byte;
+
+	page_size = sysconf(_SC_PAGESIZE);
+
+	CHECK(pipe(pipefd));
+	child = CHECK(fork());
+	if (child == 0) {
+		// Wait until the parent closes the pipe.
+		close(pipefd[1]);
+		read(pipefd[0], &byte, 1);
+		_exit(0);
+	}
+	CHECK(close(pipefd[0]));
+}
+END_SETUP()
+
+FN_TEST(kcmp_resources)
+{
+	int fd;
+
+	TEST_RES(kcmp(getpid(), getpid(), KCMP_VM, 0, 0), _ret == 0);
+	TEST_RES(kcmp(getpid(), getpid(), KCMP_FILES, 0, 0), _ret == 0);
+
+	// The child has its own address space and file table after `fork`.
+	TEST_RES(kcmp(getpid(), child, KCMP_VM, 0, 0), _ret != 0);
+	TEST_RES(kcmp(getpid(), child, KCMP_FILES, 0, 0), _ret != 0);
+
+	// The results are consistent, so that the resources can be sorted.
+	TEST_RES(kcmp(getpid(), child, KCMP_VM, 0, 0),
+		 _ret == 3 - kcmp(child, getpid(), KCMP_VM, 0, 0));
+
+	// The file description is shared by the duplicated descriptors, and by
+	// the inherited descriptors.
+	fd = TEST_SUCC(dup(pipefd[1]));
+	TEST_RES(kcmp(getpid(), getpid(), KCMP_FILE, pipefd[1], fd),
+		 _ret == 0);
+	TEST_RES(kcmp(getpid(), child, KCMP_FILE, STDOUT_FILENO,
+		      STDOUT_FILENO),
+		 _ret == 0);
+	TEST_RES(kcmp(getpid(), getpid(), KCMP_FILE, pipefd[1], 0), _ret != 0);
+	TEST_SUCC(close(fd));
+}
+END_TEST()
+
+FN_TEST(kcmp_errors)
+{
+	TEST_ERRNO(kcmp(getpid(), getpid(), KCMP_FILE, 0, 1000), EBADF);
+	TEST_ERRNO(kcmp(getpid(), getpid(), 100, 0, 0), EINVAL);
+	TEST_ERRNO(kcmp(getpid(), 0x7fffffff, KCMP_VM, 0, 0), ESRCH);
+}
+END_TEST()
+
+static uint64_t pagemap_entry(int fd, void *addr)
+{
+	uint64_t entry;
+
+	if (pread(fd, &entry, sizeof(entry),
+		  (uintptr_t)addr / page_size * sizeof(entry)) != sizeof(entry))
+		return -1;
+	return entry;
+}
+
+FN_TEST(pagemap)
+{
+	int fd;
+	char *private, *shared;
+	uint64_t entry;
+
+	fd = TEST_SUCC(open("/proc/self/pagemap", O_RDONLY));
+	private = (char *)TEST_SUCC((long)mmap(NULL, page_size * 2,
+					       PROT_READ | PROT_WRITE,
+					       MAP_PRIVATE | MAP_ANONYMOUS, -1,
+					       0));
+	shared = (char *)TEST_SUCC((long)mmap(NULL, page_size,
+					      PROT_READ | PROT_WRITE,
+					      MAP_SHARED | MAP_ANONYMOUS, -1,
+					      0));
+	private[0] = 1;
+	shared[0] = 1;
+
+	TEST_RES(pagemap_entry(fd, private),
+		 (_ret & PM_PRESENT) && (_ret & PM_MMAP_EXCLUSIVE) &&
+			 !(_ret & PM_FILE));
+	TEST_RES(pagemap_entry(fd, private + page_size), _ret == 0);
+	TEST_RES(pagemap_entry(fd, shared),
+		 (_ret & PM_PRESENT) && (_ret & PM_FILE));
+
+	// The entries are read as a whole.
+	TEST_ERRNO(pread(fd, &entry, sizeof(entry),
+			 (uintptr_t)private / page_size * sizeof(entry) + 1),
+		   EINVAL);
+
+	TEST_SUCC(munmap(private, page_size * 2));
+	TEST_SUCC(munmap(shared, page_size));
+	TEST_SUCC(close(fd));
+}
+END_TEST()
+
+static int read_mm_map(struct mm_map *map)
+{
+	static const int fields[] = { 26, 27, 28, 45, 46, 47, 48, 49, 50, 51 };
+	uint64_t values[sizeof(fields) / sizeof(fields[0])];
+	char buf[1024], *pos;
+	int fd, field, i;
+	ssize_t len;
+
+	fd = open("/proc/self/stat", O_RDONLY);
+	if (fd < 0)
+		return -1;
+	len = read(fd, buf, sizeof(buf) - 1);
+	close(fd);
+	if (len < 0)
+		return -1;
+	buf[len] = '\0';
+
+	// The fields start from the third one after the command name.
+	pos = strrchr(buf, ')') + 2;
+	for (field = 3, i = 0; i < sizeof(fields) / sizeof(fields[0]);
+	     ++field) {
+		if (field == fields[i])
+			values[i++] = strtoull(pos, NULL, 10);
+		pos = strchr(pos, ' ') + 1;
+	}
+
+	memset(map, 0, sizeof(*map));
+	map->start_code = values[0];
+	map->end_code = values[1];
+	map->start_stack = values[2];
+	map->start_data = values[3];
+	map->end_data = values[4];
+	map->start_brk = values[5];
+	map->brk = (uintptr_t)sbrk(0);
+	map->arg_start = values[6];
+	map->arg_end = values[7];
+	map->env_start = values[8];
+	map->env_end = values[9];
+	map->exe_fd = -1;
+	return 0;
+}
+
+FN_TEST(set_mm_map)
+{
+	struct mm_map map, check;
+	static char args[] = "restored\0args";
+	unsigned int size;
+
+	TEST_RES(prctl(PR_SET_MM, PR_SET_MM_MAP_SIZE, &size, 0, 0),
+		 _ret == 0 && size == sizeof(struct mm_map));
+
+	TEST_SUCC(read_mm_map(&map));
+	TEST_RES(map.start_code, _ret < map.end_code);
+	TEST_RES(map.arg_start, _ret < map.arg_end);
+	TEST_SUCC(prctl(PR_SET_MM, PR_SET_MM_MAP, &map, sizeof(map), 0));
+
+	// The arguments are moved, e.g., to where they are in the checkpoint.
+	map.arg_start = (uintptr_t)args;
+	map.arg_end = (uintptr_t)args + sizeof(args);
+	TEST_SUCC(prctl(PR_SET_MM, PR_SET_MM_MAP, &map, sizeof(map), 0));
+	TEST_RES(read_mm_map(&check), _ret == 0 &&
+					      check.arg_start == map.arg_start &&
+					      check.arg_end == map.arg_end);
+
+	// The ranges must be ordered.
+	map.arg_end = map.arg_start - 1;
+	TEST_ERRNO(prctl(PR_SET_MM, PR_SET_MM_MAP, &map, sizeof(map), 0),
+		   EINVAL);
+	map.arg_end = map.arg_start;
+	map.end_code = map.start_code;
+	TEST_ERRNO(prctl(PR_SET_MM, PR_SET_MM_MAP, &map, sizeof(map), 0),
+		   EINVAL);
+
+	// The size must match the structure.
+	TEST_ERRNO(prctl(PR_SET_MM, PR_SET_MM_MAP, &map, sizeof(map) - 8, 0),
+		   EINVAL);
+}
+END_TEST()
+
+FN_TEST(set_mm_fields)
+{
+	struct mm_map map, check;
+	int fd;
+
+	TEST_SUCC(read_mm_map(&map));
+	TEST_SUCC(prctl(PR_SET_MM, PR_SET_MM_ENV_END, map.env_start, 0, 0));
+	TEST_RES(read_mm_map(&check), _ret == 0 &&
+					      check.env_start == map.env_start &&
+					      check.env_end == map.env_start);
+	TEST_SUCC(prctl(PR_SET_MM, PR_SET_MM_ENV_END, map.env_end, 0, 0));
+
+	TEST_ERRNO(prctl(PR_SET_MM, PR_SET_MM_ENV_END, map.env_end, 1, 0),
+		   EINVAL);
+	TEST_ERRNO(prctl(PR_SET_MM, PR_SET_MM_END_CODE, map.start_code, 0, 0),
+		   EINVAL);
+
+	// Only regular files can be the executable.
+	fd = TEST_SUCC(open("/", O_RDONLY));
+	TEST_ERRNO(prctl(PR_SET_MM, PR_SET_MM_EXE_FILE, fd, 0, 0), EACCES);
+	TEST_SUCC(close(fd));
+}
+END_TEST()
+
+FN_TEST(auxv)
+{
+	uint64_t auxv[128], check[128];
+	ssize_t len;
+	int fd, i;
+
+	fd = TEST_SUCC(open("/proc/self/auxv", O_RDONLY));
+	len = TEST_RES(read(fd, auxv, sizeof(auxv)),
+		       _ret > 0 && _ret < sizeof(auxv) &&
+			       (_ret & (2 * sizeof(uint64_t) - 1)) == 0);
+	TEST_SUCC(close(fd));
+
+	// The auxiliary vector ends with `AT_NULL`.
+	TEST_RES(auxv[len / sizeof(uint64_t) - 2], _ret == AT_NULL);
+	for (i = 0; auxv[i] != AT_NULL && auxv[i] != AT_PAGESZ; i += 2)
+		;
+	TEST_RES(auxv[i + 1], _ret == page_size);
+
+	// The vector can be set back as it is.
+	TEST_SUCC(prctl(PR_SET_MM, PR_SET_MM_AUXV, auxv, len, 0));
+	fd = TEST_SUCC(open("/proc/self/auxv", O_RDONLY));
+	TEST_RES(read(fd, check, sizeof(check)),
+		 _ret == len && memcmp(auxv, check, len) == 0);
+	TEST_SUCC(close(fd));
+}
+END_TEST()
+
+FN_SETUP(reap)
+{
+	int status;
+
+	CHECK(close(pipefd[1]));
+	CHECK_WITH(waitpid(child, &status, 0),
+		   _ret == child && WIFEXITED(status) &&
+			   WEXITSTATUS(status) == 0);
+}
+END_SETUP()
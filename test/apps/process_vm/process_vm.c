@@ -0,0 +1,126 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include "../network/test.h"
+#include <errno.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/mman.h>
+#include <sys/uio.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+static char buffer[64] = "hello from the child";
+static char *unmapped;
+static pid_t child;
+static int pipefd[2];
+
+FN_SETUP(spawn)
+{
+	long page_size = sysconf(_SC_PAGESIZE);
+	char byte;
+
+	unmapped = mmap(NULL, page_size * 2, PROT_READ | PROT_WRITE,
+			MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+	if (unmapped == MAP_FAILED) {
+		perror("mmap");
+		exit(EXIT_FAILURE);
+	}
+	CHECK(munmap(unmapped + page_size, page_size));
+	unmapped += page_size;
+
+	CHECK(pipe(pipefd));
+	child = CHECK(fork());
+	if (child == 0) {
+		// Wait until the parent closes the pipe.
+		close(pipefd[1]);
+		read(pipefd[0], &byte, 1);
+		_exit(strcmp(buffer, "hello from the parent") == 0 ? 0 : 1);
+	}
+	CHECK(close(pipefd[0]));
+
+	// The child shares no pages with the parent after this.
+	strcpy(buffer, "the parent does not see this");
+}
+END_SETUP()
+
+FN_TEST(read_child)
+{
+	char local[64] = {};
+	struct iovec local_iov[2] = {
+		{ .iov_base = local, .iov_len = 6 },
+		{ .iov_base = local + 6, .iov_len = sizeof(local) - 6 },
+	};
+	struct iovec remote_iov = { .iov_base = buffer,
+				    .iov_len = sizeof(buffer) };
+
+	TEST_RES(process_vm_readv(child, local_iov, 2, &remote_iov, 1, 0),
+		 _ret == sizeof(buffer) &&
+			 strcmp(local, "hello from the child") == 0);
+}
+END_TEST()
+
+FN_TEST(write_child)
+{
+	char local[] = "hello from the parent";
+	char check[sizeof(local)] = {};
+	struct iovec local_iov = { .iov_base = local, .iov_len = sizeof(local) };
+	struct iovec remote_iov = { .iov_base = buffer,
+				    .iov_len = sizeof(local) };
+	struct iovec check_iov = { .iov_base = check, .iov_len = sizeof(check) };
+
+	TEST_RES(process_vm_writev(child, &local_iov, 1, &remote_iov, 1, 0),
+		 _ret == sizeof(local));
+	TEST_RES(process_vm_readv(child, &check_iov, 1, &remote_iov, 1, 0),
+		 _ret == sizeof(check) && strcmp(check, local) == 0);
+}
+END_TEST()
+
+FN_TEST(partial_transfer)
+{
+	char local[16];
+	struct iovec local_iov = { .iov_base = local, .iov_len = sizeof(local) };
+	struct iovec remote_iov[2] = {
+		{ .iov_base = unmapped - 8, .iov_len = 16 },
+		{ .iov_base = buffer, .iov_len = 8 },
+	};
+
+	// The transfer stops at the first remote page that is not mapped.
+	TEST_RES(process_vm_readv(child, &local_iov, 1, remote_iov, 2, 0),
+		 _ret == 8);
+
+	remote_iov[0].iov_base = unmapped;
+	TEST_ERRNO(process_vm_readv(child, &local_iov, 1, remote_iov, 2, 0),
+		   EFAULT);
+}
+END_TEST()
+
+FN_TEST(errors)
+{
+	char local[8];
+	struct iovec local_iov = { .iov_base = local, .iov_len = sizeof(local) };
+	struct iovec remote_iov = { .iov_base = buffer,
+				    .iov_len = sizeof(local) };
+
+	TEST_ERRNO(process_vm_readv(child, &local_iov, 1, &remote_iov, 1, 1),
+		   EINVAL);
+	TEST_ERRNO(process_vm_readv(child, &local_iov, 1025, &remote_iov, 1, 0),
+		   EINVAL);
+	TEST_ERRNO(process_vm_readv(0x7fffffff, &local_iov, 1, &remote_iov, 1,
+				    0),
+		   ESRCH);
+}
+END_TEST()
+
+FN_SETUP(reap)
+{
+	int status;
+
+	CHECK(close(pipefd[1]));
+	CHECK_WITH(waitpid(child, &status, 0),
+		   _ret == child && WIFEXITED(status) &&
+			   WEXITSTATUS(status) == 0);
+}
+END_SETUP()
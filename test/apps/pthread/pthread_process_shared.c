@@ -0,0 +1,175 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include "../network/test.h"
+#include <errno.h>
+#include <fcntl.h>
+#include <linux/futex.h>
+#include <pthread.h>
+#include <stdint.h>
+#include <sys/mman.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+#include <time.h>
+#include <unistd.h>
+
+#define FILE_PATH "/tmp/pthread_process_shared"
+
+static long futex(uint32_t *addr, int op, uint32_t val,
+		  const struct timespec *timeout)
+{
+	return syscall(SYS_futex, addr, op, val, timeout, NULL, 0);
+}
+
+static int wait_child(pid_t pid)
+{
+	int status;
+
+	if (waitpid(pid, &status, 0) != pid)
+		return -1;
+	return WIFEXITED(status) ? WEXITSTATUS(status) : -1;
+}
+
+FN_TEST(futex_at_different_addresses)
+{
+	int fd;
+	uint32_t *addr1, *addr2;
+	struct timespec timeout = { .tv_sec = 5 };
+	pid_t pid;
+	int i;
+
+	fd = TEST_SUCC(open(FILE_PATH, O_RDWR | O_CREAT | O_TRUNC, 0600));
+	TEST_SUCC(unlink(FILE_PATH));
+	TEST_SUCC(ftruncate(fd, getpagesize()));
+	addr1 = mmap(NULL, getpagesize(), PROT_READ | PROT_WRITE, MAP_SHARED,
+		     fd, 0);
+	addr2 = mmap(NULL, getpagesize(), PROT_READ | PROT_WRITE, MAP_SHARED,
+		     fd, 0);
+	TEST_RES(0, addr1 != MAP_FAILED && addr2 != MAP_FAILED &&
+			    addr1 != addr2);
+
+	pid = TEST_SUCC(fork());
+	if (pid == 0) {
+		// The child waits on the first mapping.
+		_exit(futex(&addr1[1], FUTEX_WAIT, 0, &timeout) == 0 ? 0 : 1);
+	}
+
+	// The parent wakes the child via the second mapping.
+	for (i = 0; i < 500; ++i) {
+		if (futex(&addr2[1], FUTEX_WAKE, 1, NULL) == 1)
+			break;
+		usleep(10 * 1000);
+	}
+	TEST_RES(i, _ret < 500);
+	TEST_RES(wait_child(pid), _ret == 0);
+
+	// A private futex is not shared with the other processes.
+	pid = TEST_SUCC(fork());
+	if (pid == 0) {
+		timeout.tv_sec = 0;
+		timeout.tv_nsec = 100 * 1000 * 1000;
+		_exit(futex(&addr1[1], FUTEX_WAIT_PRIVATE, 0, &timeout) == -1 &&
+				      errno == ETIMEDOUT ?
+			      0 :
+			      1);
+	}
+	TEST_RES(futex(&addr2[1], FUTEX_WAKE_PRIVATE, 1, NULL), _ret == 0);
+	TEST_RES(wait_child(pid), _ret == 0);
+
+	TEST_ERRNO(futex((uint32_t *)((char *)addr1 + 1), FUTEX_WAKE, 1, NULL),
+		   EINVAL);
+
+	TEST_SUCC(munmap(addr1, getpagesize()));
+	TEST_SUCC(munmap(addr2, getpagesize()));
+	TEST_SUCC(close(fd));
+}
+END_TEST()
+
+struct shared_state {
+	pthread_mutex_t mutex;
+	pthread_cond_t cond;
+	int flag;
+};
+
+static struct shared_state *state;
+
+FN_SETUP(init_shared_state)
+{
+	pthread_mutexattr_t mutex_attr;
+	pthread_condattr_t cond_attr;
+
+	state = mmap(NULL, sizeof(*state), PROT_READ | PROT_WRITE,
+		     MAP_SHARED | MAP_ANONYMOUS, -1, 0);
+	CHECK_WITH(state == MAP_FAILED ? -1 : 0, _ret == 0);
+
+	CHECK(pthread_mutexattr_init(&mutex_attr));
+	CHECK(pthread_mutexattr_setpshared(&mutex_attr,
+					   PTHREAD_PROCESS_SHARED));
+	CHECK(pthread_mutexattr_setrobust(&mutex_attr, PTHREAD_MUTEX_ROBUST));
+	CHECK(pthread_mutex_init(&state->mutex, &mutex_attr));
+
+	CHECK(pthread_condattr_init(&cond_attr));
+	CHECK(pthread_condattr_setpshared(&cond_attr, PTHREAD_PROCESS_SHARED));
+	CHECK(pthread_cond_init(&state->cond, &cond_attr));
+}
+END_SETUP()
+
+FN_TEST(mutex_and_cond)
+{
+	pid_t pid;
+
+	TEST_RES(pthread_mutex_lock(&state->mutex), _ret == 0);
+
+	pid = TEST_SUCC(fork());
+	if (pid == 0) {
+		if (pthread_mutex_lock(&state->mutex) != 0)
+			_exit(1);
+		state->flag = 1;
+		pthread_cond_signal(&state->cond);
+		pthread_mutex_unlock(&state->mutex);
+		_exit(0);
+	}
+
+	// The child is blocked until the parent waits on the condition variable.
+	while (!state->flag)
+		TEST_RES(pthread_cond_wait(&state->cond, &state->mutex),
+			 _ret == 0);
+	TEST_RES(pthread_mutex_unlock(&state->mutex), _ret == 0);
+	TEST_RES(wait_child(pid), _ret == 0);
+}
+END_TEST()
+
+FN_TEST(robust_mutex)
+{
+	int pipefd[2];
+	pid_t pid;
+	char c;
+
+	TEST_SUCC(pipe(pipefd));
+
+	pid = TEST_SUCC(fork());
+	if (pid == 0) {
+		// The child exits while holding the mutex.
+		if (pthread_mutex_lock(&state->mutex) != 0)
+			_exit(1);
+		if (write(pipefd[1], "x", 1) != 1)
+			_exit(1);
+		usleep(100 * 1000);
+		_exit(0);
+	}
+
+	// The parent is woken up after the child exits.
+	TEST_RES(read(pipefd[0], &c, 1), _ret == 1);
+	TEST_RES(pthread_mutex_lock(&state->mutex), _ret == EOWNERDEAD);
+	TEST_RES(pthread_mutex_consistent(&state->mutex), _ret == 0);
+	TEST_RES(pthread_mutex_unlock(&state->mutex), _ret == 0);
+	TEST_RES(wait_child(pid), _ret == 0);
+
+	TEST_RES(pthread_mutex_lock(&state->mutex), _ret == 0);
+	TEST_RES(pthread_mutex_unlock(&state->mutex), _ret == 0);
+
+	TEST_SUCC(close(pipefd[0]));
+	TEST_SUCC(close(pipefd[1]));
+}
+END_TEST()
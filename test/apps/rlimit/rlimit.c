@@ -0,0 +1,184 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include "../network/test.h"
+#include <errno.h>
+#include <fcntl.h>
+#include <signal.h>
+#include <string.h>
+#include <sys/resource.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define NOFILE_LIMIT 16
+
+static struct rlimit saved_nofile;
+
+FN_SETUP(save_limits)
+{
+	CHECK(getrlimit(RLIMIT_NOFILE, &saved_nofile));
+}
+END_SETUP()
+
+FN_TEST(prlimit)
+{
+	struct rlimit old_limit, new_limit;
+
+	TEST_RES(prlimit(0, RLIMIT_NOFILE, NULL, &old_limit),
+		 old_limit.rlim_cur == saved_nofile.rlim_cur &&
+			 old_limit.rlim_max == saved_nofile.rlim_max);
+
+	// The old limit is returned before the new limit takes effect.
+	new_limit.rlim_cur = NOFILE_LIMIT;
+	new_limit.rlim_max = saved_nofile.rlim_max;
+	TEST_RES(prlimit(getpid(), RLIMIT_NOFILE, &new_limit, &old_limit),
+		 old_limit.rlim_cur == saved_nofile.rlim_cur);
+	TEST_RES(getrlimit(RLIMIT_NOFILE, &old_limit),
+		 old_limit.rlim_cur == NOFILE_LIMIT);
+	TEST_SUCC(setrlimit(RLIMIT_NOFILE, &saved_nofile));
+
+	TEST_ERRNO(prlimit(0x7fffffff, RLIMIT_NOFILE, NULL, &old_limit), ESRCH);
+
+	new_limit.rlim_cur = saved_nofile.rlim_max + 1;
+	new_limit.rlim_max = saved_nofile.rlim_max;
+	TEST_ERRNO(setrlimit(RLIMIT_NOFILE, &new_limit), EINVAL);
+
+	new_limit.rlim_cur = new_limit.rlim_max = RLIM_INFINITY;
+	TEST_ERRNO(setrlimit(RLIMIT_NOFILE, &new_limit), EPERM);
+}
+END_TEST()
+
+FN_TEST(nofile)
+{
+	struct rlimit limit = { NOFILE_LIMIT, saved_nofile.rlim_max };
+	int fds[NOFILE_LIMIT], pipefds[2];
+	int i, nr_fds = 0;
+
+	TEST_SUCC(setrlimit(RLIMIT_NOFILE, &limit));
+
+	for (i = 0; i < NOFILE_LIMIT; ++i) {
+		fds[i] = open("/dev/null", O_RDONLY);
+		if (fds[i] < 0)
+			break;
+		++nr_fds;
+	}
+	TEST_RES(nr_fds, _ret > 0 && _ret < NOFILE_LIMIT &&
+				 fds[nr_fds - 1] == NOFILE_LIMIT - 1);
+
+	TEST_ERRNO(open("/dev/null", O_RDONLY), EMFILE);
+	TEST_ERRNO(dup(0), EMFILE);
+	TEST_ERRNO(pipe(pipefds), EMFILE);
+	TEST_ERRNO(fcntl(0, F_DUPFD, NOFILE_LIMIT), EINVAL);
+	TEST_ERRNO(dup2(0, NOFILE_LIMIT), EBADF);
+
+	for (i = 0; i < nr_fds; ++i)
+		TEST_SUCC(close(fds[i]));
+	TEST_SUCC(setrlimit(RLIMIT_NOFILE, &saved_nofile));
+}
+END_TEST()
+
+FN_TEST(fsize)
+{
+	struct rlimit limit = { 100, RLIM_INFINITY };
+	char buf[200];
+	int fd;
+
+	memset(buf, 'a', sizeof(buf));
+	TEST_RES((long)signal(SIGXFSZ, SIG_IGN), _ret != (long)SIG_ERR);
+	fd = TEST_SUCC(open("/tmp/rlimit_fsize", O_WRONLY | O_CREAT | O_TRUNC,
+			    0644));
+
+	TEST_SUCC(setrlimit(RLIMIT_FSIZE, &limit));
+	// The write is truncated at the limit.
+	TEST_RES(write(fd, buf, sizeof(buf)), _ret == 100);
+	TEST_ERRNO(write(fd, buf, sizeof(buf)), EFBIG);
+	limit.rlim_cur = RLIM_INFINITY;
+	TEST_SUCC(setrlimit(RLIMIT_FSIZE, &limit));
+
+	TEST_RES(write(fd, buf, sizeof(buf)), _ret == sizeof(buf));
+	TEST_SUCC(close(fd));
+	TEST_SUCC(unlink("/tmp/rlimit_fsize"));
+	TEST_RES((long)signal(SIGXFSZ, SIG_DFL), _ret != (long)SIG_ERR);
+}
+END_TEST()
+
+FN_TEST(data)
+{
+	struct rlimit saved_limit, limit = { 0, RLIM_INFINITY };
+
+	TEST_SUCC(getrlimit(RLIMIT_DATA, &saved_limit));
+	limit.rlim_max = saved_limit.rlim_max;
+
+	TEST_SUCC(setrlimit(RLIMIT_DATA, &limit));
+	TEST((long)sbrk(4096), ENOMEM, _ret == -1);
+	TEST_SUCC(setrlimit(RLIMIT_DATA, &saved_limit));
+
+	TEST_RES((long)sbrk(4096), _ret != -1);
+}
+END_TEST()
+
+// Runs `func` in a child process and returns its exit code.
+static int run_in_child(int (*func)(void))
+{
+	int status;
+	pid_t pid;
+
+	pid = fork();
+	if (pid < 0)
+		return -1;
+	if (pid == 0)
+		_exit(func());
+
+	if (waitpid(pid, &status, 0) != pid || !WIFEXITED(status))
+		return -1;
+	return WEXITSTATUS(status);
+}
+
+static int fork_without_privileges(void)
+{
+	struct rlimit limit = { 0, 0 };
+	pid_t pid;
+
+	if (setrlimit(RLIMIT_NPROC, &limit) < 0 || setuid(65534) < 0)
+		return 1;
+
+	pid = fork();
+	if (pid == 0)
+		_exit(0);
+	if (pid > 0 || errno != EAGAIN)
+		return 2;
+	return 0;
+}
+
+FN_TEST(nproc)
+{
+	TEST_RES(run_in_child(fork_without_privileges), _ret == 0);
+}
+END_TEST()
+
+static volatile sig_atomic_t has_received_sigxcpu;
+
+static void handle_sigxcpu(int signum)
+{
+	has_received_sigxcpu = 1;
+}
+
+static int exhaust_cpu_time(void)
+{
+	struct rlimit limit = { 1, RLIM_INFINITY };
+
+	if (signal(SIGXCPU, handle_sigxcpu) == SIG_ERR ||
+	    setrlimit(RLIMIT_CPU, &limit) < 0)
+		return 1;
+
+	while (!has_received_sigxcpu)
+		;
+	return 0;
+}
+
+FN_TEST(cpu)
+{
+	TEST_RES(run_in_child(exhaust_cpu_time), _ret == 0);
+}
+END_TEST()
@@ -0,0 +1,218 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+#include <errno.h>
+#include <sched.h>
+#include <signal.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/syscall.h>
+#include <sys/time.h>
+#include <ucontext.h>
+#include <unistd.h>
+
+#define CHECK(cond)                                                   \
+	do {                                                          \
+		if (!(cond)) {                                        \
+			fprintf(stderr, "%s:%d: check failed: %s\n", \
+				__FILE__, __LINE__, #cond);           \
+			exit(EXIT_FAILURE);                           \
+		}                                                     \
+	} while (0)
+
+#if defined(__riscv)
+#define RSEQ_SIG 0xf1401073
+#elif defined(__x86_64__)
+#define RSEQ_SIG 0x53053053
+#else
+#error "unsupported architecture"
+#endif
+
+#define STR_(x) #x
+#define STR(x) STR_(x)
+
+#define RSEQ_FLAG_UNREGISTER 1
+#define RSEQ_CPU_ID_UNINITIALIZED ((uint32_t)-1)
+
+// The layout of `struct rseq`, which is not taken from `<linux/rseq.h>`
+// because the definition there differs between the versions.
+struct rseq_area {
+	uint32_t cpu_id_start;
+	uint32_t cpu_id;
+	uint64_t rseq_cs;
+	uint32_t flags;
+	uint32_t node_id;
+	uint32_t mm_cid;
+	char padding[4];
+} __attribute__((aligned(32)));
+
+static volatile struct rseq_area area = {
+	.cpu_id = RSEQ_CPU_ID_UNINITIALIZED,
+};
+
+static int sys_rseq(int flags, uint32_t sig)
+{
+	return syscall(SYS_rseq, &area, sizeof(area), flags, sig);
+}
+
+static unsigned int getcpu_raw(void)
+{
+	unsigned int cpu;
+
+	CHECK(syscall(SYS_getcpu, &cpu, NULL, NULL) == 0);
+	return cpu;
+}
+
+// Test that the area is filled in once it is registered, and that the
+// registration is checked against the area and the signature.
+static void test_register(void)
+{
+	CHECK(sys_rseq(0, RSEQ_SIG) == 0);
+	CHECK(area.cpu_id == getcpu_raw());
+	CHECK(area.cpu_id_start == area.cpu_id);
+
+	CHECK(sys_rseq(0, RSEQ_SIG) == -1 && errno == EBUSY);
+	CHECK(sys_rseq(0, RSEQ_SIG + 1) == -1 && errno == EPERM);
+	CHECK(sys_rseq(RSEQ_FLAG_UNREGISTER, RSEQ_SIG + 1) == -1 &&
+	      errno == EPERM);
+
+	CHECK(sys_rseq(RSEQ_FLAG_UNREGISTER, RSEQ_SIG) == 0);
+	CHECK(area.cpu_id == RSEQ_CPU_ID_UNINITIALIZED);
+	CHECK(sys_rseq(RSEQ_FLAG_UNREGISTER, RSEQ_SIG) == -1 &&
+	      errno == EINVAL);
+
+	CHECK(sys_rseq(0, RSEQ_SIG) == 0);
+	printf("rseq registration test passed\n");
+}
+
+// Test that `cpu_id` follows the thread when it is migrated to each of
+// the CPUs that it may run on.
+static void test_migration(void)
+{
+	cpu_set_t orig_mask, mask;
+	int nr_cpus = 0;
+
+	CHECK(sched_getaffinity(0, sizeof(orig_mask), &orig_mask) == 0);
+	for (int cpu = 0; cpu < CPU_SETSIZE; cpu++) {
+		if (!CPU_ISSET(cpu, &orig_mask))
+			continue;
+
+		CPU_ZERO(&mask);
+		CPU_SET(cpu, &mask);
+		CHECK(sched_setaffinity(0, sizeof(mask), &mask) == 0);
+
+		// The thread is migrated the next time it is switched out.
+		for (int i = 0; i < 1000 && getcpu_raw() != cpu; i++)
+			sched_yield();
+		CHECK(getcpu_raw() == cpu);
+		CHECK(area.cpu_id == cpu);
+		CHECK(area.cpu_id_start == cpu);
+		nr_cpus++;
+	}
+	CHECK(sched_setaffinity(0, sizeof(orig_mask), &orig_mask) == 0);
+
+	printf("rseq migration test passed on %d CPU(s)\n", nr_cpus);
+}
+
+// The abort handler of the critical section, which is defined below.
+extern char rseq_abort_ip[];
+
+static volatile int is_aborted_by_signal;
+
+static void alarm_handler(int signum, siginfo_t *info, void *ucontext)
+{
+	ucontext_t *uc = ucontext;
+	struct itimerval timer = {
+		.it_value = { .tv_sec = 0, .tv_usec = 10000 },
+	};
+
+#if defined(__riscv)
+	uintptr_t pc = uc->uc_mcontext.__gregs[REG_PC];
+#else
+	uintptr_t pc = uc->uc_mcontext.gregs[REG_RIP];
+#endif
+	// The signal handler returns to the abort handler if the signal has
+	// interrupted the critical section. Otherwise, try again.
+	if (pc == (uintptr_t)rseq_abort_ip)
+		is_aborted_by_signal = 1;
+	else
+		CHECK(setitimer(ITIMER_REAL, &timer, NULL) == 0);
+}
+
+// Test that a signal aborts the critical section. The critical section
+// spins forever, so it can only be left through the abort handler, which
+// is also reached if the thread is switched out.
+static void test_abort_on_signal(void)
+{
+	struct sigaction sa;
+	struct itimerval timer = {
+		.it_value = { .tv_sec = 0, .tv_usec = 10000 },
+	};
+
+	memset(&sa, 0, sizeof(sa));
+	sa.sa_sigaction = alarm_handler;
+	sa.sa_flags = SA_SIGINFO;
+	CHECK(sigaction(SIGALRM, &sa, NULL) == 0);
+	CHECK(setitimer(ITIMER_REAL, &timer, NULL) == 0);
+
+	while (!is_aborted_by_signal) {
+		// The descriptor is `struct rseq_cs`, whose abort handler is
+		// preceded by the signature.
+		__asm__ __volatile__(
+			".pushsection .data.rseq_cs, \"aw\"\n"
+			".balign 32\n"
+			"3:\n"
+			".long 0, 0\n"
+			".quad 1f, 2f - 1f, rseq_abort_ip\n"
+			".popsection\n"
+#if defined(__riscv)
+			"la t0, 3b\n"
+			"sd t0, %[cs]\n"
+			"1:\n"
+			"j 1b\n"
+#else
+			"leaq 3b(%%rip), %%rax\n"
+			"movq %%rax, %[cs]\n"
+			"1:\n"
+			"jmp 1b\n"
+#endif
+			"2:\n"
+			".long " STR(RSEQ_SIG) "\n"
+			".globl rseq_abort_ip\n"
+			"rseq_abort_ip:\n"
+			: [cs] "=m"(area.rseq_cs)
+			:
+#if defined(__riscv)
+			: "t0", "memory"
+#else
+			: "rax", "memory"
+#endif
+		);
+
+		// The descriptor is cleared by the kernel when it is checked.
+		CHECK(area.rseq_cs == 0);
+	}
+
+	printf("rseq abort test passed\n");
+}
+
+int main(int argc, char *argv[])
+{
+	// glibc registers its own area for each thread, which must be disabled
+	// so that this test can register one.
+	if (getenv("GLIBC_TUNABLES") == NULL) {
+		CHECK(setenv("GLIBC_TUNABLES", "glibc.pthread.rseq=0", 1) == 0);
+		execv(argv[0], argv);
+		perror("execv");
+		exit(EXIT_FAILURE);
+	}
+
+	test_register();
+	test_migration();
+	test_abort_on_signal();
+
+	CHECK(sys_rseq(RSEQ_FLAG_UNREGISTER, RSEQ_SIG) == 0);
+	return 0;
+}
@@ -0,0 +1,115 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include "../network/test.h"
+#include <fcntl.h>
+#include <poll.h>
+#include <stdio.h>
+#include <string.h>
+#include <unistd.h>
+
+static const char *files[] = {
+	"/proc/pressure/cpu",
+	"/proc/pressure/memory",
+	"/proc/pressure/io",
+};
+
+static int check_line(const char *line, const char *kind)
+{
+	char name[8];
+	float avg10, avg60, avg300;
+	unsigned long long total;
+
+	if (sscanf(line, "%7s avg10=%f avg60=%f avg300=%f total=%llu", name,
+		   &avg10, &avg60, &avg300, &total) != 5)
+		return -1;
+	if (strcmp(name, kind) != 0)
+		return -1;
+	if (avg10 < 0 || avg10 > 100 || avg60 < 0 || avg60 > 100 ||
+	    avg300 < 0 || avg300 > 100)
+		return -1;
+	return 0;
+}
+
+static int check_file(int fd)
+{
+	char buf[256];
+	char *full;
+	ssize_t len;
+
+	len = read(fd, buf, sizeof(buf) - 1);
+	if (len <= 0)
+		return -1;
+	buf[len] = '\0';
+
+	full = strchr(buf, '\n');
+	if (full == NULL)
+		return -1;
+	full++;
+	if (check_line(buf, "some") < 0 || check_line(full, "full") < 0)
+		return -1;
+
+	// The file ends after the two lines.
+	return read(fd, buf, sizeof(buf)) == 0 ? 0 : -1;
+}
+
+FN_TEST(read_pressure)
+{
+	int fd;
+
+	for (int i = 0; i < 3; i++) {
+		fd = TEST_SUCC(open(files[i], O_RDONLY));
+		TEST_SUCC(check_file(fd));
+
+		// The file can be read again after seeking to the start.
+		TEST_RES(lseek(fd, 0, SEEK_SET), _ret == 0);
+		TEST_SUCC(check_file(fd));
+
+		TEST_SUCC(close(fd));
+	}
+}
+END_TEST()
+
+FN_TEST(invalid_triggers)
+{
+	static const char *triggers[] = {
+		"foo 100000 1000000", "some", "some 100000",
+		"some 0 1000000",     "some 100000 1000",
+		"some 2000000 1000000",
+	};
+	int fd;
+
+	fd = TEST_SUCC(open("/proc/pressure/cpu", O_RDWR));
+	for (int i = 0; i < sizeof(triggers) / sizeof(triggers[0]); i++)
+		TEST_ERRNO(write(fd, triggers[i], strlen(triggers[i])), EINVAL);
+	TEST_SUCC(close(fd));
+}
+END_TEST()
+
+FN_TEST(poll_triggers)
+{
+	const char *trigger = "some 150000 2000000";
+	struct pollfd pfd;
+	int fd;
+
+	fd = TEST_SUCC(open("/proc/pressure/io", O_RDWR));
+
+	// The files without triggers report errors.
+	pfd.fd = fd;
+	pfd.events = POLLPRI;
+	TEST_RES(poll(&pfd, 1, 0),
+		 _ret == 1 && (pfd.revents & (POLLERR | POLLPRI)) ==
+				      (POLLERR | POLLPRI));
+
+	TEST_RES(write(fd, trigger, strlen(trigger) + 1),
+		 _ret == strlen(trigger) + 1);
+	TEST_ERRNO(write(fd, trigger, strlen(trigger)), EBUSY);
+
+	// There is no I/O, so the trigger is not notified.
+	pfd.revents = 0;
+	TEST_RES(poll(&pfd, 1, 100), _ret == 0 && pfd.revents == 0);
+
+	TEST_SUCC(close(fd));
+}
+END_TEST()
@@ -61,6 +61,14 @@ echo "Start fdatasync test......"
 test_fdatasync
 echo "All fdatasync test passed."
 
+echo "Start fs-verity test......"
+fsverity/fsverity
+echo "All fs-verity test passed."
+
+echo "Start flashfs test......"
+flashfs/flashfs
+echo "All flashfs test passed."
+
 pipe/pipe_err
 pipe/short_rw
 epoll/epoll_err
@@ -33,5 +33,13 @@ sleep 0.2
 ./tcp_poll
 ./udp_err
 ./unix_err
+./unix_scm
+./netlink_route
+./ipv6
+./raw_socket
+./packet_socket
+./netfilter
+./tun
+./wireguard
 
 echo "All network test passed"
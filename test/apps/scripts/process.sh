@@ -10,30 +10,56 @@ cd ${SCRIPT_DIR}/..
 echo "Start process test......"
 # These test programs are sorted by name.
 tests="
+adjtimex/adjtimex
+aslr/aslr
+binfmt/binfmt
+boot/boot_params
 clone3/clone_exit_signal
 clone3/clone_no_exit_signal
 clone3/clone_process
 cpu_affinity/cpu_affinity
+cpu_time/cpu_time
+evdev/evdev
+eventfd2/eventfd2
 execve/execve
 exit/exit_code
 exit/exit_procfs
-eventfd2/eventfd2
 fork/fork
 fork_c/fork
 getpid/getpid
+hello_pie/auxv
 hello_pie/hello
 hello_world/hello_world
+itimer/posix_timer
 itimer/setitimer
 itimer/timer_create
+keyring/keyctl
+landlock/landlock
+mmap/hugetlb
+mmap/ksm
+mmap/mlock_and_mincore
 mmap/mmap_and_fork
 mmap/mmap_shared_filebacked
 mmap/mmap_readahead
+mmap/mmap_zero_page
+mqueue/mqueue
+process_vm/checkpoint
+process_vm/process_vm
+pthread/pthread_process_shared
 pthread/pthread_test
 pty/open_pty
+rlimit/rlimit
+rseq/rseq
+sched/pressure
 sched/sched_attr
 shm/posix_shm
 signal_c/parent_death_signal
 signal_c/signal_test
+sysctl/sysctl
+sysv_ipc/sysv_msg
+sysv_ipc/sysv_sem
+sysv_ipc/sysv_shm
+tls/tls
 "
 
 for testcase in ${tests}
@@ -0,0 +1,161 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include "../network/test.h"
+#include <fcntl.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/resource.h>
+#include <sys/socket.h>
+#include <sys/stat.h>
+#include <unistd.h>
+
+static int write_sysctl(const char *path, const char *value)
+{
+	int fd, ret;
+
+	fd = open(path, O_WRONLY);
+	if (fd < 0)
+		return -1;
+	ret = write(fd, value, strlen(value));
+	close(fd);
+	return ret;
+}
+
+static long read_sysctl(const char *path)
+{
+	char buf[32] = { 0 };
+	int fd, ret;
+
+	fd = open(path, O_RDONLY);
+	if (fd < 0)
+		return -1;
+	ret = read(fd, buf, sizeof(buf) - 1);
+	close(fd);
+	if (ret <= 0 || buf[ret - 1] != '\n')
+		return -1;
+	return strtol(buf, NULL, 10);
+}
+
+static int file_mode(const char *path)
+{
+	struct stat st;
+
+	if (stat(path, &st) < 0)
+		return -1;
+	return st.st_mode & 0777;
+}
+
+FN_TEST(modes)
+{
+	TEST_RES(file_mode("/proc/sys/kernel/cap_last_cap"), _ret == 0444);
+	TEST_RES(file_mode("/proc/sys/kernel/randomize_va_space"),
+		 _ret == 0644);
+	TEST_RES(file_mode("/proc/sys/fs/nr_open"), _ret == 0644);
+	TEST_RES(file_mode("/proc/sys/net/core/rmem_max"), _ret == 0644);
+}
+END_TEST()
+
+FN_TEST(int_values)
+{
+	TEST_RES(read_sysctl("/proc/sys/kernel/cap_last_cap"), _ret == 40);
+
+	// The values are checked against the ranges.
+	TEST_ERRNO(write_sysctl("/proc/sys/fs/nr_open", "1"), EINVAL);
+	TEST_ERRNO(write_sysctl("/proc/sys/fs/nr_open", "many"), EINVAL);
+	TEST_ERRNO(write_sysctl("/proc/sys/net/core/rmem_max", "100"), EINVAL);
+	TEST_RES(read_sysctl("/proc/sys/fs/nr_open"), _ret == 1048576);
+}
+END_TEST()
+
+FN_TEST(bool_values)
+{
+	long panic_on_oops;
+
+	panic_on_oops = TEST_RES(read_sysctl("/proc/sys/kernel/panic_on_oops"),
+				 _ret == 0 || _ret == 1);
+	TEST_SUCC(write_sysctl("/proc/sys/kernel/panic_on_oops",
+			       panic_on_oops ? "1\n" : "0\n"));
+	TEST_RES(read_sysctl("/proc/sys/kernel/panic_on_oops"),
+		 _ret == panic_on_oops);
+}
+END_TEST()
+
+FN_TEST(nr_open)
+{
+	struct rlimit rlimit;
+
+	TEST_SUCC(getrlimit(RLIMIT_NOFILE, &rlimit));
+	TEST_SUCC(write_sysctl("/proc/sys/fs/nr_open", "64"));
+	TEST_RES(read_sysctl("/proc/sys/fs/nr_open"), _ret == 64);
+
+	// The current limit is kept, but it cannot be set again.
+	TEST_ERRNO(setrlimit(RLIMIT_NOFILE, &rlimit), EPERM);
+
+	TEST_SUCC(write_sysctl("/proc/sys/fs/nr_open", "1048576"));
+	TEST_SUCC(setrlimit(RLIMIT_NOFILE, &rlimit));
+}
+END_TEST()
+
+FN_TEST(pipe_max_size)
+{
+	// The size is rounded up to a power-of-two number of pages.
+	TEST_SUCC(write_sysctl("/proc/sys/fs/pipe-max-size", "5000"));
+	TEST_RES(read_sysctl("/proc/sys/fs/pipe-max-size"), _ret == 8192);
+
+	TEST_SUCC(write_sysctl("/proc/sys/fs/pipe-max-size", "1048576"));
+	TEST_RES(read_sysctl("/proc/sys/fs/pipe-max-size"), _ret == 1048576);
+}
+END_TEST()
+
+FN_TEST(rmem_max)
+{
+	int sk, size = 65536;
+	socklen_t len = sizeof(size);
+
+	TEST_SUCC(write_sysctl("/proc/sys/net/core/rmem_max", "4096"));
+	TEST_RES(read_sysctl("/proc/sys/net/core/rmem_max"), _ret == 4096);
+
+	// The size is clamped by `rmem_max` and then doubled.
+	sk = TEST_SUCC(socket(AF_INET, SOCK_DGRAM, 0));
+	TEST_SUCC(setsockopt(sk, SOL_SOCKET, SO_RCVBUF, &size, sizeof(size)));
+	TEST_RES(getsockopt(sk, SOL_SOCKET, SO_RCVBUF, &size, &len),
+		 _ret == 0 && size == 8192);
+	TEST_SUCC(close(sk));
+
+	TEST_SUCC(write_sysctl("/proc/sys/net/core/rmem_max", "212992"));
+}
+END_TEST()
+
+static int read_console_loglevel(void)
+{
+	char buf[64] = { 0 };
+	int fd, ret, levels[4];
+
+	fd = open("/proc/sys/kernel/printk", O_RDONLY);
+	if (fd < 0)
+		return -1;
+	ret = read(fd, buf, sizeof(buf) - 1);
+	close(fd);
+	if (ret < 0 || sscanf(buf, "%d %d %d %d", &levels[0], &levels[1],
+			      &levels[2], &levels[3]) != 4)
+		return -1;
+	return levels[0];
+}
+
+FN_TEST(printk)
+{
+	char buf[16];
+	int level;
+
+	level = TEST_RES(read_console_loglevel(), _ret >= 0);
+
+	// Only the current console log level is changed.
+	snprintf(buf, sizeof(buf), "%d 4 1 7\n", level);
+	TEST_SUCC(write_sysctl("/proc/sys/kernel/printk", buf));
+	TEST_RES(read_console_loglevel(), _ret == level);
+	TEST_ERRNO(write_sysctl("/proc/sys/kernel/printk", "loud"), EINVAL);
+}
+END_TEST()
@@ -0,0 +1,100 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include "../network/test.h"
+#include <errno.h>
+#include <string.h>
+#include <sys/ipc.h>
+#include <sys/msg.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+struct message {
+	long mtype;
+	char mtext[16];
+};
+
+static int msqid;
+
+FN_SETUP(create)
+{
+	msqid = CHECK(msgget(IPC_PRIVATE, IPC_CREAT | 0600));
+}
+END_SETUP()
+
+static int send(long mtype, const char *text)
+{
+	struct message msg = { .mtype = mtype };
+
+	strcpy(msg.mtext, text);
+	return msgsnd(msqid, &msg, strlen(text) + 1, IPC_NOWAIT);
+}
+
+FN_TEST(send_and_receive)
+{
+	struct message msg;
+	struct msqid_ds ds;
+
+	TEST_SUCC(send(1, "one"));
+	TEST_SUCC(send(2, "two"));
+	TEST_SUCC(send(3, "three"));
+	TEST_RES(msgctl(msqid, IPC_STAT, &ds),
+		 ds.msg_qnum == 3 && ds.msg_lspid == getpid());
+
+	TEST_RES(msgrcv(msqid, &msg, sizeof(msg.mtext), 2, IPC_NOWAIT),
+		 _ret == 4 && msg.mtype == 2 && strcmp(msg.mtext, "two") == 0);
+	TEST_RES(msgrcv(msqid, &msg, sizeof(msg.mtext), 1, MSG_EXCEPT),
+		 _ret == 6 && msg.mtype == 3);
+	TEST_ERRNO(msgrcv(msqid, &msg, 2, 0, IPC_NOWAIT), E2BIG);
+	TEST_RES(msgrcv(msqid, &msg, 2, -5, MSG_NOERROR),
+		 _ret == 2 && msg.mtype == 1 && memcmp(msg.mtext, "on", 2) == 0);
+
+	TEST_ERRNO(msgrcv(msqid, &msg, sizeof(msg.mtext), 0, IPC_NOWAIT),
+		   ENOMSG);
+	TEST_ERRNO(send(0, "zero"), EINVAL);
+}
+END_TEST()
+
+FN_TEST(blocking_receive)
+{
+	struct message msg;
+	int status;
+	pid_t pid;
+
+	pid = TEST_SUCC(fork());
+	if (pid == 0) {
+		usleep(100 * 1000);
+		_exit(send(7, "late") < 0);
+	}
+
+	TEST_RES(msgrcv(msqid, &msg, sizeof(msg.mtext), 7, 0),
+		 _ret == 5 && strcmp(msg.mtext, "late") == 0);
+	TEST_RES(waitpid(pid, &status, 0), _ret == pid && status == 0);
+}
+END_TEST()
+
+FN_TEST(queue_full)
+{
+	struct message msg;
+	struct msqid_ds ds;
+
+	TEST_SUCC(msgctl(msqid, IPC_STAT, &ds));
+	ds.msg_qbytes = 8;
+	TEST_SUCC(msgctl(msqid, IPC_SET, &ds));
+
+	TEST_SUCC(send(1, "1234567"));
+	TEST_ERRNO(send(1, ""), EAGAIN);
+	TEST_SUCC(msgrcv(msqid, &msg, sizeof(msg.mtext), 0, 0));
+}
+END_TEST()
+
+FN_TEST(remove)
+{
+	struct message msg;
+
+	TEST_SUCC(msgctl(msqid, IPC_RMID, NULL));
+	TEST_ERRNO(msgrcv(msqid, &msg, sizeof(msg.mtext), 0, IPC_NOWAIT),
+		   EINVAL);
+}
+END_TEST()
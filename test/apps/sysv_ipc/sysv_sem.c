@@ -0,0 +1,92 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#include "../network/test.h"
+#include <errno.h>
+#include <sys/ipc.h>
+#include <sys/sem.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define NSEMS 2
+
+static int semid;
+
+FN_SETUP(create)
+{
+	semid = CHECK(semget(IPC_PRIVATE, NSEMS, IPC_CREAT | 0600));
+}
+END_SETUP()
+
+FN_TEST(setall_getall)
+{
+	unsigned short vals[NSEMS] = { 3, 5 };
+	struct semid_ds ds;
+
+	TEST_SUCC(semctl(semid, 0, SETALL, vals));
+	vals[0] = vals[1] = 0;
+	TEST_RES(semctl(semid, 0, GETALL, vals), vals[0] == 3 && vals[1] == 5);
+	TEST_RES(semctl(semid, 1, GETVAL), _ret == 5);
+
+	TEST_ERRNO(semctl(semid, 0, SETVAL, 32768), ERANGE);
+	TEST_RES(semctl(semid, 0, IPC_STAT, &ds), ds.sem_nsems == NSEMS);
+
+	TEST_ERRNO(semget(IPC_PRIVATE, 0, IPC_CREAT | 0600), EINVAL);
+}
+END_TEST()
+
+FN_TEST(undo_on_exit)
+{
+	struct sembuf op = { .sem_num = 0, .sem_op = -2, .sem_flg = SEM_UNDO };
+	int status;
+	pid_t pid;
+
+	TEST_SUCC(semctl(semid, 0, SETVAL, 3));
+
+	pid = TEST_SUCC(fork());
+	if (pid == 0) {
+		if (semop(semid, &op, 1) < 0)
+			_exit(1);
+		_exit(semctl(semid, 0, GETVAL) == 1 ? 0 : 2);
+	}
+	TEST_RES(waitpid(pid, &status, 0), _ret == pid && status == 0);
+
+	// The adjustment of the child is undone when it exits.
+	TEST_RES(semctl(semid, 0, GETVAL), _ret == 3);
+}
+END_TEST()
+
+FN_TEST(setval_clears_undo)
+{
+	struct sembuf op = { .sem_num = 1, .sem_op = 1, .sem_flg = SEM_UNDO };
+	int status;
+	pid_t pid;
+
+	TEST_SUCC(semctl(semid, 1, SETVAL, 0));
+
+	pid = TEST_SUCC(fork());
+	if (pid == 0) {
+		if (semop(semid, &op, 1) < 0)
+			_exit(1);
+		if (semctl(semid, 1, SETVAL, 4) < 0)
+			_exit(2);
+		_exit(0);
+	}
+	TEST_RES(waitpid(pid, &status, 0), _ret == pid && status == 0);
+
+	TEST_RES(semctl(semid, 1, GETVAL), _ret == 4);
+}
+END_TEST()
+
+FN_TEST(nowait)
+{
+	struct sembuf op = { .sem_num = 1, .sem_op = -5, .sem_flg = IPC_NOWAIT };
+
+	TEST_ERRNO(semop(semid, &op, 1), EAGAIN);
+}
+END_TEST()
+
+FN_SETUP(remove)
+{
+	CHECK(semctl(semid, 0, IPC_RMID));
+}
+END_SETUP()
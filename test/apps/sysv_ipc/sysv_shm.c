@@ -0,0 +1,105 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#include "../network/test.h"
+#include <errno.h>
+#include <string.h>
+#include <sys/ipc.h>
+#include <sys/shm.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define SEGMENT_SIZE 8192
+
+static int shmid;
+
+FN_SETUP(create)
+{
+	shmid = CHECK(shmget(IPC_PRIVATE, SEGMENT_SIZE, IPC_CREAT | 0600));
+}
+END_SETUP()
+
+FN_TEST(get)
+{
+	int key = 0x5eed;
+	int id;
+
+	id = TEST_SUCC(shmget(key, SEGMENT_SIZE, IPC_CREAT | 0600));
+	TEST_RES(shmget(key, SEGMENT_SIZE, 0), _ret == id);
+	TEST_ERRNO(shmget(key, SEGMENT_SIZE, IPC_CREAT | IPC_EXCL | 0600),
+		   EEXIST);
+	TEST_ERRNO(shmget(key, SEGMENT_SIZE * 2, 0), EINVAL);
+	TEST_SUCC(shmctl(id, IPC_RMID, NULL));
+
+	TEST_ERRNO(shmget(key, SEGMENT_SIZE, 0), ENOENT);
+	TEST_ERRNO(shmget(IPC_PRIVATE, 0, IPC_CREAT | 0600), EINVAL);
+}
+END_TEST()
+
+static char *attach(int id, int flags)
+{
+	return shmat(id, NULL, flags);
+}
+
+FN_TEST(attach_and_fork)
+{
+	struct shmid_ds ds;
+	char *addr;
+	int status;
+	pid_t pid;
+
+	addr = attach(shmid, 0);
+	TEST_RES(addr != (char *)-1, _ret);
+	memset(addr, 0, SEGMENT_SIZE);
+
+	TEST_RES(shmctl(shmid, IPC_STAT, &ds),
+		 ds.shm_segsz == SEGMENT_SIZE && ds.shm_nattch == 1 &&
+			 ds.shm_cpid == getpid());
+
+	pid = TEST_SUCC(fork());
+	if (pid == 0) {
+		strcpy(addr, "hello");
+		// The segment is attached again by the fork.
+		_exit(0);
+	}
+	TEST_RES(waitpid(pid, &status, 0), _ret == pid && status == 0);
+	TEST_RES(strcmp(addr, "hello"), _ret == 0);
+
+	TEST_SUCC(shmdt(addr));
+	TEST_ERRNO(shmdt(addr), EINVAL);
+	TEST_RES(shmctl(shmid, IPC_STAT, &ds), ds.shm_nattch == 0);
+}
+END_TEST()
+
+FN_TEST(attach_readonly)
+{
+	char *addr;
+
+	addr = attach(shmid, SHM_RDONLY);
+	TEST_RES(addr != (char *)-1, _ret);
+	TEST_RES(strcmp(addr, "hello"), _ret == 0);
+	TEST_SUCC(shmdt(addr));
+
+	TEST_ERRNO(shmat(shmid, (void *)1, 0) == (void *)-1 ? -1 : 0, EINVAL);
+	TEST_ERRNO(shmat(shmid, NULL, SHM_REMAP) == (void *)-1 ? -1 : 0,
+		   EINVAL);
+}
+END_TEST()
+
+FN_TEST(remove_attached)
+{
+	struct shmid_ds ds;
+	char *addr;
+
+	addr = attach(shmid, 0);
+	TEST_RES(addr != (char *)-1, _ret);
+
+	// The segment is destroyed after the last detach.
+	TEST_SUCC(shmctl(shmid, IPC_RMID, NULL));
+	TEST_RES(shmctl(shmid, IPC_STAT, &ds),
+		 ds.shm_nattch == 1 && (ds.shm_perm.mode & SHM_DEST));
+	TEST_RES(strcmp(addr, "hello"), _ret == 0);
+
+	TEST_SUCC(shmdt(addr));
+	TEST_ERRNO(shmctl(shmid, IPC_STAT, &ds), EINVAL);
+}
+END_TEST()
@@ -0,0 +1,143 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+#include <linux/futex.h>
+#include <pthread.h>
+#include <sched.h>
+#include <signal.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/syscall.h>
+#include <unistd.h>
+
+#define CHECK(cond)                                                   \
+	do {                                                          \
+		if (!(cond)) {                                        \
+			fprintf(stderr, "%s:%d: check failed: %s\n", \
+				__FILE__, __LINE__, #cond);           \
+			exit(EXIT_FAILURE);                           \
+		}                                                     \
+	} while (0)
+
+static __thread volatile int tls_value;
+
+// Test `set_tid_address`, which returns the TID of the caller.
+static void test_set_tid_address(void)
+{
+	static pid_t clear_tid = -1;
+
+	pid_t tid = syscall(SYS_set_tid_address, &clear_tid);
+	CHECK(tid == syscall(SYS_gettid));
+	CHECK(clear_tid == -1);
+
+	printf("set_tid_address test passed\n");
+}
+
+static volatile void *handler_tp;
+static volatile int handler_value;
+
+static void tls_signal_handler(int signum)
+{
+	handler_tp = __builtin_thread_pointer();
+	handler_value = tls_value;
+	tls_value = -1;
+}
+
+// Test that the thread pointer is kept when a signal handler runs and
+// returns, which should not be affected by the kernel.
+static void *test_tls_across_signals(void *arg)
+{
+	int value = (int)(intptr_t)arg;
+	void *tp = __builtin_thread_pointer();
+
+	tls_value = value;
+	CHECK(pthread_kill(pthread_self(), SIGUSR1) == 0);
+
+	CHECK(handler_tp == tp);
+	CHECK(handler_value == value);
+	CHECK(__builtin_thread_pointer() == tp);
+	CHECK(tls_value == -1);
+
+	return NULL;
+}
+
+static void run_tls_across_signals(void)
+{
+	struct sigaction sa;
+	pthread_t thread;
+
+	memset(&sa, 0, sizeof(sa));
+	sa.sa_handler = tls_signal_handler;
+	CHECK(sigaction(SIGUSR1, &sa, NULL) == 0);
+
+	test_tls_across_signals((void *)1);
+
+	CHECK(pthread_create(&thread, NULL, test_tls_across_signals,
+			     (void *)2) == 0);
+	CHECK(pthread_join(thread, NULL) == 0);
+
+	printf("TLS across signals test passed\n");
+}
+
+#define CHILD_STACK_SIZE (64 * 1024)
+
+// The fake thread control block, whose address is set as the thread pointer
+// of the child by `CLONE_SETTLS`. On x86-64, `__builtin_thread_pointer`
+// reads the pointer from `%fs:0`, so the first word of the block must point
+// to the block itself. On RISC-V, it reads the `tp` register directly, and
+// the self-pointer is harmless.
+static uintptr_t child_tls[64] __attribute__((aligned(64)));
+static volatile void *child_tp;
+static volatile pid_t child_tid = -1;
+
+// The child must not access the thread-local storage of the C library,
+// since its thread pointer points to the fake block.
+static int clone_settls_child(void *arg)
+{
+	child_tp = __builtin_thread_pointer();
+	return 0;
+}
+
+// Test that `clone` with `CLONE_SETTLS` sets the thread pointer of the
+// child, and that `CLONE_CHILD_CLEARTID` clears the TID when the child
+// exits.
+static void test_clone_settls(void)
+{
+	int flags = CLONE_VM | CLONE_FS | CLONE_FILES | CLONE_SIGHAND |
+		    CLONE_THREAD | CLONE_SYSVSEM | CLONE_SETTLS |
+		    CLONE_CHILD_SETTID | CLONE_CHILD_CLEARTID;
+	void *tp = __builtin_thread_pointer();
+	char *stack;
+	pid_t tid;
+
+	stack = malloc(CHILD_STACK_SIZE);
+	CHECK(stack != NULL);
+	child_tls[0] = (uintptr_t)child_tls;
+
+	tid = clone(clone_settls_child, stack + CHILD_STACK_SIZE, flags, NULL,
+		    NULL, child_tls, &child_tid);
+	CHECK(tid > 0);
+
+	// Wait until the child exits and clears its TID.
+	while (child_tid != 0)
+		syscall(SYS_futex, &child_tid, FUTEX_WAIT, tid, NULL, NULL, 0);
+
+	CHECK(child_tp == child_tls);
+	CHECK(__builtin_thread_pointer() == tp);
+
+	free(stack);
+
+	printf("clone with CLONE_SETTLS test passed\n");
+}
+
+int main(void)
+{
+	test_set_tid_address();
+	run_tls_across_signals();
+	test_clone_settls();
+
+	printf("All TLS tests passed\n");
+	return 0;
+}
@@ -0,0 +1,134 @@
+#!/bin/sh
+
+# SPDX-License-Identifier: MPL-2.0
+
+# Runs the curated gVisor and LTP system call tests and compares the results with the baseline
+# of the known failures in `diff_test/expected_failures`. The script exits with 1 if any test
+# case regresses, which is reported to the host through the exit status of QEMU.
+
+SCRIPT_DIR=$(dirname "$0")
+TEST_TMP_DIR=${SYSCALL_TEST_DIR:-/tmp}
+GVISOR_BIN_DIR=$SCRIPT_DIR/tests
+LTP_ROOT=$SCRIPT_DIR/ltp
+LTP_BIN_DIR=$LTP_ROOT/testcases/bin
+BLOCKLIST_DIR=$SCRIPT_DIR/blocklists
+DIFF_TEST_DIR=$SCRIPT_DIR/diff_test
+RESULTS=$SCRIPT_DIR/diff_results
+# The exit code of LTP test cases that are not applicable to the system.
+LTP_TCONF=32
+BLOCK=""
+REGRESSIONS=0
+FIXED=0
+SKIPPED=0
+GREEN='\033[0;32m'
+RED='\033[0;31m'
+YELLOW='\033[0;33m'
+NC='\033[0m'
+
+list_tests(){
+    grep -v '^#' $DIFF_TEST_DIR/$1 | grep -v '^$'
+}
+
+get_blocklist_subtests(){
+    if [ -f $BLOCKLIST_DIR/$1 ]; then
+        BLOCK=$(grep -v '^#' $BLOCKLIST_DIR/$1 | tr '\n' ':')
+    else
+        BLOCK=""
+    fi
+
+    for extra_dir in $EXTRA_BLOCKLISTS_DIRS ; do
+        if [ -f $SCRIPT_DIR/$extra_dir/$1 ]; then
+            BLOCK="${BLOCK}:$(grep -v '^#' $SCRIPT_DIR/$extra_dir/$1 | tr '\n' ':')"
+        fi
+    done
+
+    return 0
+}
+
+is_expected_failure(){
+    grep -qx "$1" $DIFF_TEST_DIR/expected_failures
+}
+
+record_result(){
+    case_name=$1
+    ret=$2
+    if [ $ret -eq 0 ]; then
+        echo "PASS $case_name" >> $RESULTS
+        if is_expected_failure $case_name; then
+            echo -e "${YELLOW}Fixed${NC}: $case_name"
+            FIXED=$((FIXED+1))
+        fi
+    else
+        echo "FAIL $case_name" >> $RESULTS
+        if ! is_expected_failure $case_name; then
+            echo -e "${RED}Regression${NC}: $case_name"
+            REGRESSIONS=$((REGRESSIONS+1))
+        fi
+    fi
+}
+
+run_gvisor_test(){
+    echo -e "Run Test Case: gvisor/$1"
+    # The gvisor test framework utilizes the "TEST_TMPDIR" environment variable to dictate the directory's location.
+    export TEST_TMPDIR=$TEST_TMP_DIR
+    ret=0
+    if [ -f $GVISOR_BIN_DIR/$1 ]; then
+        get_blocklist_subtests $1
+        (cd $GVISOR_BIN_DIR && ./$1 --gtest_filter=-$BLOCK)
+        ret=$?
+        rm -rf $TEST_TMP_DIR/*
+    else
+        echo -e "Warning: $1 test does not exist"
+        ret=1
+    fi
+    echo ""
+    record_result gvisor/$1 $ret
+}
+
+run_ltp_test(){
+    echo -e "Run Test Case: ltp/$1"
+    # The LTP library creates the temporary directories of the test cases under "TMPDIR".
+    export LTPROOT=$LTP_ROOT
+    export TMPDIR=$TEST_TMP_DIR
+    ret=0
+    if [ -f $LTP_BIN_DIR/$1 ]; then
+        (cd $LTP_BIN_DIR && ./$1)
+        ret=$?
+        rm -rf $TEST_TMP_DIR/*
+    else
+        echo -e "Warning: $1 test does not exist"
+        ret=1
+    fi
+    echo ""
+    if [ $ret -eq $LTP_TCONF ]; then
+        echo "SKIP ltp/$1" >> $RESULTS
+        SKIPPED=$((SKIPPED+1))
+        return
+    fi
+    record_result ltp/$1 $ret
+}
+
+rm -f $RESULTS && touch $RESULTS
+rm -rf $TEST_TMP_DIR/*
+
+for test_name in $(list_tests gvisor_tests) ; do
+    run_gvisor_test $test_name
+done
+for test_name in $(list_tests ltp_tests) ; do
+    run_ltp_test $test_name
+done
+
+PASSED=$(grep -c '^PASS' $RESULTS)
+FAILED=$(grep -c '^FAIL' $RESULTS)
+echo -e "$GREEN$PASSED$NC passed, $RED$FAILED$NC failed, $SKIPPED skipped," \
+    "$RED$REGRESSIONS$NC regressed and $YELLOW$FIXED$NC fixed compared to the baseline."
+if [ $FIXED -ne 0 ]; then
+    echo "Please remove the fixed test cases from the expected failures."
+fi
+
+if [ $REGRESSIONS -ne 0 ]; then
+    echo "Syscall diff test failed."
+    exit 1
+fi
+echo "Syscall diff test passed."
+exit 0